@@ -5,6 +5,7 @@ import {
   devices,
   type Browser,
   type BrowserContext,
+  type BrowserContextOptions,
   type Page,
   type Frame,
   type Dialog,
@@ -85,6 +86,7 @@ interface PageError {
 export class BrowserManager {
   private browser: Browser | null = null;
   private isPersistentContext = false;
+  private contextOptions: BrowserContextOptions = {};
   private contexts: BrowserContext[] = [];
   private pages: Page[] = [];
   private activePageIndex = 0;
@@ -281,12 +283,16 @@ export class BrowserManager {
         executablePath: options.executablePath,
         args: baseArgs,
       });
-      context = await this.browser.newContext({
+      // Kept so contexts recreated later (storage state swaps) look the same.
+      this.contextOptions = {
         viewport,
         extraHTTPHeaders: headers,
         userAgent,
         ...(options.proxy && { proxy: options.proxy }),
         ignoreHTTPSErrors: options.ignoreHTTPSErrors ?? false,
+      };
+      context = await this.browser.newContext({
+        ...this.contextOptions,
         ...(options.storageState && { storageState: options.storageState }),
       });
     }
@@ -330,6 +336,7 @@ export class BrowserManager {
 
     this.pages = [];
     this.contexts = [];
+    this.contextOptions = {};
     this.isPersistentContext = false;
     this.activePageIndex = 0;
     this.refMap = {};
@@ -837,7 +844,11 @@ export class BrowserManager {
 
     await oldContext.close().catch(() => {});
 
-    const newContext = await this.browser.newContext({ viewport, storageState: parsed });
+    const newContext = await this.browser.newContext({
+      ...this.contextOptions,
+      viewport,
+      storageState: parsed,
+    });
     newContext.setDefaultTimeout(60000);
 
     if (contextIndex !== -1) {
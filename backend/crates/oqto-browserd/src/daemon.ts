@@ -4,9 +4,12 @@ import { BrowserManager } from "./browser.js";
 import { StreamServer } from "./stream-server.js";
 import { executeCommand, setScreencastFrameCallback } from "./actions.js";
 import {
+  type AdoptCommand,
   errorResponse,
   parseCommand,
+  successResponse,
 } from "./protocol.js";
+import { getProfileStatePath, readProfileState, saveProfileState } from "./profile.js";
 import { getPidFile, getSessionId, getSocketDir, getSocketPath, getStreamPortFile } from "./paths.js";
 
 function ensureDir(dir: string): void {
@@ -56,6 +59,17 @@ function getScreencastOptions() {
   } as const;
 }
 
+/** How often the profile state of a running browser is written back. */
+const PROFILE_SAVE_INTERVAL_MS = 30_000;
+
+interface DaemonState {
+  session: string;
+  server: net.Server;
+  streamServer: StreamServer | null;
+  /** Pre-launched for the backend's pool; waiting to be adopted. */
+  pooled: boolean;
+}
+
 export async function startDaemon(): Promise<void> {
   const session = getSessionId();
   const socketDir = getSocketDir(session);
@@ -70,23 +84,50 @@ export async function startDaemon(): Promise<void> {
   }
 
   const browser = new BrowserManager();
-  let streamServer: StreamServer | null = null;
+  const state: DaemonState = {
+    session,
+    server: net.createServer(),
+    streamServer: null,
+    pooled: boolFromEnv(process.env.AGENT_BROWSER_POOLED, false),
+  };
 
   const streamPort = parsePositiveInt(process.env.AGENT_BROWSER_STREAM_PORT, 0);
   if (streamPort > 0) {
-    streamServer = new StreamServer(browser, streamPort, getScreencastOptions());
-    await streamServer.start();
-    fs.writeFileSync(getStreamPortFile(session), String(streamPort));
+    await startStream(state, browser, streamPort);
   }
 
   // Wire screencast frame callback for actions that start screencast programmatically
   setScreencastFrameCallback((frame) => {
-    if (streamServer) {
+    if (state.streamServer) {
       // The stream server broadcasts frames; this wires the actions module
       // screencast_start/stop to the same broadcast path.
     }
   });
 
+  state.server = listen(state, browser, session);
+
+  const profileTimer = setInterval(() => {
+    const profileState = getProfileStatePath();
+    if (profileState && !state.pooled) {
+      saveProfileState(browser, profileState).catch(() => undefined);
+    }
+  }, PROFILE_SAVE_INTERVAL_MS);
+  profileTimer.unref();
+
+  const shutdownHandler = async () => {
+    await shutdown(state, browser);
+  };
+
+  process.on("SIGINT", shutdownHandler);
+  process.on("SIGTERM", shutdownHandler);
+  process.on("SIGHUP", shutdownHandler);
+
+  process.on("exit", () => {
+    cleanupSession(state.session);
+  });
+}
+
+function listen(state: DaemonState, browser: BrowserManager, session: string): net.Server {
   const server = net.createServer((socket) => {
     let buffer = "";
 
@@ -109,6 +150,13 @@ export async function startDaemon(): Promise<void> {
         }
 
         try {
+          if (parsed.command.action === "adopt") {
+            await adopt(state, browser, parsed.command as AdoptCommand);
+            const response = successResponse(parsed.command.id, { session: state.session });
+            socket.write(`${JSON.stringify(response)}\n`);
+            continue;
+          }
+
           // Auto-launch browser if not yet launched (except for launch/close commands)
           if (
             parsed.command.action !== "launch" &&
@@ -122,7 +170,7 @@ export async function startDaemon(): Promise<void> {
           socket.write(`${JSON.stringify(response)}\n`);
 
           if (parsed.command.action === "close") {
-            await shutdown(server, browser, streamServer, session);
+            await shutdown(state, browser);
             return;
           }
         } catch (error) {
@@ -138,22 +186,60 @@ export async function startDaemon(): Promise<void> {
     });
   });
 
-  const socketPath = getSocketPath(session);
-  server.listen(socketPath, () => {
+  server.listen(getSocketPath(session), () => {
     fs.writeFileSync(getPidFile(session), String(process.pid));
   });
+  return server;
+}
 
-  const shutdownHandler = async () => {
-    await shutdown(server, browser, streamServer, session);
-  };
+async function startStream(state: DaemonState, browser: BrowserManager, port: number): Promise<void> {
+  state.streamServer = new StreamServer(browser, port, getScreencastOptions());
+  await state.streamServer.start();
+  fs.writeFileSync(getStreamPortFile(state.session), String(port));
+}
 
-  process.on("SIGINT", shutdownHandler);
-  process.on("SIGTERM", shutdownHandler);
-  process.on("SIGHUP", shutdownHandler);
+/**
+ * Take over `command.session` with this pre-launched browser: load the
+ * session owner's profile, start its stream server and move the control
+ * socket to the session's directory.
+ */
+async function adopt(state: DaemonState, browser: BrowserManager, command: AdoptCommand): Promise<void> {
+  if (!state.pooled) {
+    throw new Error("Only pooled daemons can be adopted");
+  }
+  if (!command.session) {
+    throw new Error("adopt requires a session");
+  }
 
-  process.on("exit", () => {
-    cleanupSession(session);
-  });
+  const previous = state.session;
+  const previousServer = state.server;
+
+  if (!browser.isLaunched()) {
+    await autoLaunch(browser);
+  }
+  if (command.profileState) {
+    process.env.AGENT_BROWSER_PROFILE_STATE = command.profileState;
+    const saved = readProfileState(command.profileState);
+    if (saved) {
+      await browser.applyStorageState(saved);
+    }
+  }
+
+  ensureDir(getSocketDir(command.session));
+  cleanupSession(command.session);
+  process.env.AGENT_BROWSER_SESSION = command.session;
+  state.session = command.session;
+  state.pooled = false;
+
+  if (command.streamPort && command.streamPort > 0) {
+    await startStream(state, browser, command.streamPort);
+  }
+  state.server = listen(state, browser, command.session);
+
+  // Existing connections (including the one adopting us) stay open.
+  previousServer.close();
+  cleanupSession(previous);
+  fs.rmSync(getSocketDir(previous), { recursive: true, force: true });
 }
 
 function parseExtensions(): string[] | undefined {
@@ -190,13 +276,24 @@ async function autoLaunch(browser: BrowserManager): Promise<void> {
   const ignoreHTTPSErrors = boolFromEnv(process.env.AGENT_BROWSER_IGNORE_HTTPS_ERRORS, false);
   const allowFileAccess = boolFromEnv(process.env.AGENT_BROWSER_ALLOW_FILE_ACCESS, false);
 
+  // A saved profile is loaded as storage state; it cannot be combined with
+  // a Chromium profile dir or extensions (both use persistent contexts).
+  const savedProfile = getProfileStatePath();
+  const profileState =
+    savedProfile &&
+    !process.env.AGENT_BROWSER_PROFILE &&
+    !process.env.AGENT_BROWSER_EXTENSIONS &&
+    readProfileState(savedProfile)
+      ? savedProfile
+      : undefined;
+
   await browser.launch({
     headless,
     viewport: { width: viewportWidth, height: viewportHeight },
     executablePath: process.env.AGENT_BROWSER_EXECUTABLE_PATH,
     extensions: parseExtensions(),
     profile: process.env.AGENT_BROWSER_PROFILE,
-    storageState: process.env.AGENT_BROWSER_STATE,
+    storageState: process.env.AGENT_BROWSER_STATE ?? profileState,
     args: parseArgs(),
     userAgent: process.env.AGENT_BROWSER_USER_AGENT,
     proxy: parseProxy(),
@@ -205,26 +302,26 @@ async function autoLaunch(browser: BrowserManager): Promise<void> {
   });
 }
 
-async function shutdown(
-  server: net.Server,
-  browser: BrowserManager,
-  streamServer: StreamServer | null,
-  session: string,
-): Promise<void> {
+async function shutdown(state: DaemonState, browser: BrowserManager): Promise<void> {
   try {
-    if (streamServer) {
-      await streamServer.stop();
+    if (state.streamServer) {
+      await state.streamServer.stop();
     }
   } catch {
     // ignore
   }
 
+  const profileState = getProfileStatePath();
+  if (profileState && !state.pooled) {
+    await saveProfileState(browser, profileState).catch(() => undefined);
+  }
+
   await browser.close().catch(() => undefined);
 
   await new Promise<void>((resolve) => {
-    server.close(() => resolve());
+    state.server.close(() => resolve());
   });
 
-  cleanupSession(session);
+  cleanupSession(state.session);
   process.exit(0);
 }
@@ -389,6 +389,22 @@ function buildPayload(command: string, args: string[]): Record<string, unknown>
     case "setcontent":
       return { id, action: "setcontent", html: args.join(" ") };
 
+    // --- Pool ---
+    case "adopt": {
+      const flag = (name: string) => {
+        const idx = args.indexOf(name);
+        return idx === -1 ? undefined : args[idx + 1];
+      };
+      const streamPort = flag("--stream-port");
+      return {
+        id,
+        action: "adopt",
+        session: args[0],
+        profileState: flag("--profile-state"),
+        streamPort: streamPort ? Number.parseInt(streamPort, 10) : undefined,
+      };
+    }
+
     default:
       throw new Error(`Unsupported command: ${command}`);
   }
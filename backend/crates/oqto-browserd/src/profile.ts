@@ -0,0 +1,39 @@
+/**
+ * Persistent browser profile state.
+ *
+ * When AGENT_BROWSER_PROFILE_STATE points at a file, the daemon loads it as
+ * Playwright storage state (cookies, localStorage) at launch and writes it
+ * back periodically and on shutdown, so logins survive across sessions.
+ */
+
+import fs from "node:fs";
+import path from "node:path";
+import type { BrowserManager } from "./browser.js";
+
+export function getProfileStatePath(): string | undefined {
+  return process.env.AGENT_BROWSER_PROFILE_STATE || undefined;
+}
+
+/** Saved state if the file exists and holds valid JSON. */
+export function readProfileState(filePath: string | undefined): string | undefined {
+  if (!filePath || !fs.existsSync(filePath)) return undefined;
+  try {
+    const raw = fs.readFileSync(filePath, "utf8");
+    JSON.parse(raw);
+    return raw;
+  } catch (error) {
+    const message = error instanceof Error ? error.message : String(error);
+    console.warn(`[oqto-browserd] Ignoring unreadable profile state ${filePath}: ${message}`);
+    return undefined;
+  }
+}
+
+/** Write the browser's storage state atomically (owner-only). */
+export async function saveProfileState(browser: BrowserManager, filePath: string): Promise<void> {
+  if (!browser.isLaunched()) return;
+  const state = await browser.storageState();
+  fs.mkdirSync(path.dirname(filePath), { recursive: true, mode: 0o700 });
+  const tmp = `${filePath}.${process.pid}.tmp`;
+  fs.writeFileSync(tmp, state, { mode: 0o600 });
+  fs.renameSync(tmp, filePath);
+}
@@ -744,6 +744,16 @@ export interface UserAgentCommand extends BaseCommand {
   userAgent: string;
 }
 
+// Pool adoption (handled by the daemon itself, only accepted by pooled daemons)
+export interface AdoptCommand extends BaseCommand {
+  action: "adopt";
+  /** Session the pre-launched daemon takes over. */
+  session: string;
+  /** Persistent profile state to load now and save on exit. */
+  profileState?: string;
+  streamPort?: number;
+}
+
 // Union of all commands
 export type Command =
   | LaunchCommand
@@ -862,7 +872,8 @@ export type Command =
   | InputKeyboardCommand
   | InputTouchCommand
   | UserAgentCommand
-  | EvalHandleCommand;
+  | EvalHandleCommand
+  | AdoptCommand;
 
 // --- Response types ---
 
@@ -914,6 +925,7 @@ const KNOWN_ACTIONS = new Set([
   "input_mouse", "input_keyboard", "input_touch",
   "bringtofront", "pause",
   "evalhandle",
+  "adopt",
 ]);
 
 export type CommandParseResult =
@@ -0,0 +1,218 @@
+//! HTTP and WebSocket clients for the API under test.
+
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use anyhow::{Context, Result, anyhow, bail};
+use futures::{SinkExt, StreamExt};
+use oqto_protocol::commands::{Command, CommandPayload};
+use reqwest::{Method, StatusCode};
+use serde_json::Value;
+use tokio::sync::{Mutex as AsyncMutex, watch};
+use tokio::task::JoinHandle;
+use tokio_tungstenite::tungstenite::Message;
+
+/// Time to wait for the response to a WebSocket command.
+const COMMAND_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// HTTP client acting as one user.
+#[derive(Debug, Clone)]
+pub struct Api {
+    http: reqwest::Client,
+    base_url: String,
+    token: Option<String>,
+}
+
+impl Api {
+    pub fn new(base_url: &str) -> Self {
+        Self {
+            http: reqwest::Client::new(),
+            base_url: base_url.trim_end_matches('/').to_string(),
+            token: None,
+        }
+    }
+
+    pub fn base_url(&self) -> &str {
+        &self.base_url
+    }
+
+    pub fn token(&self) -> Option<&str> {
+        self.token.as_deref()
+    }
+
+    pub fn set_token(&mut self, token: String) {
+        self.token = Some(token);
+    }
+
+    /// Send a request and return its status and body (JSON, or the text as
+    /// a JSON string).
+    pub async fn request(
+        &self,
+        method: Method,
+        path: &str,
+        body: Option<&Value>,
+    ) -> Result<(StatusCode, Value)> {
+        let url = format!("{}{}", self.base_url, path);
+        let mut request = self.http.request(method.clone(), &url);
+        if let Some(token) = &self.token {
+            request = request.bearer_auth(token);
+        }
+        if let Some(body) = body {
+            request = request.json(body);
+        }
+        let response = request
+            .send()
+            .await
+            .with_context(|| format!("{method} {url}"))?;
+        let status = response.status();
+        let text = response.text().await.unwrap_or_default();
+        let body = serde_json::from_str(&text).unwrap_or(Value::String(text));
+        Ok((status, body))
+    }
+
+    /// Connect to the multiplexed WebSocket as this user.
+    pub async fn connect(&self) -> Result<EventStream> {
+        let token = self
+            .token
+            .as_deref()
+            .ok_or_else(|| anyhow!("log in or register before connecting"))?;
+        let ws_base = self
+            .base_url
+            .replacen("http://", "ws://", 1)
+            .replacen("https://", "wss://", 1);
+        let url = format!("{ws_base}/api/ws/mux?token={token}");
+        let (socket, _) = tokio_tungstenite::connect_async(url.as_str())
+            .await
+            .context("connecting to /api/ws/mux")?;
+        let (sink, mut stream) = socket.split();
+
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let (received, _) = watch::channel(0usize);
+        let received = Arc::new(received);
+        let reader = {
+            let events = Arc::clone(&events);
+            let received = Arc::clone(&received);
+            tokio::spawn(async move {
+                while let Some(message) = stream.next().await {
+                    let text = match message {
+                        Ok(Message::Text(text)) => text,
+                        Ok(Message::Close(_)) | Err(_) => break,
+                        Ok(_) => continue,
+                    };
+                    let Ok(event) = serde_json::from_str::<Value>(&text) else {
+                        continue;
+                    };
+                    if event.get("channel").and_then(Value::as_str) != Some("agent") {
+                        continue;
+                    }
+                    let count = {
+                        let mut events = events.lock().unwrap();
+                        events.push(event);
+                        events.len()
+                    };
+                    received.send_replace(count);
+                }
+            })
+        };
+
+        Ok(EventStream {
+            sink: AsyncMutex::new(sink),
+            events,
+            received,
+            reader,
+        })
+    }
+}
+
+type WsSink = futures::stream::SplitSink<
+    tokio_tungstenite::WebSocketStream<tokio_tungstenite::MaybeTlsStream<tokio::net::TcpStream>>,
+    Message,
+>;
+
+/// A WebSocket connection recording every agent event it receives.
+pub struct EventStream {
+    sink: AsyncMutex<WsSink>,
+    events: Arc<Mutex<Vec<Value>>>,
+    received: Arc<watch::Sender<usize>>,
+    reader: JoinHandle<()>,
+}
+
+impl EventStream {
+    /// Events received so far.
+    pub fn events(&self) -> Vec<Value> {
+        self.events.lock().unwrap().clone()
+    }
+
+    /// Wait until `check` finds what it is looking for in the events
+    /// received so far, or `timeout` passes.
+    pub async fn wait_for<T>(
+        &self,
+        timeout: Duration,
+        mut check: impl FnMut(&[Value]) -> Option<T>,
+    ) -> Option<T> {
+        let mut received = self.received.subscribe();
+        let deadline = tokio::time::Instant::now() + timeout;
+        loop {
+            received.mark_unchanged();
+            let found = check(&self.events.lock().unwrap());
+            if found.is_some() {
+                return found;
+            }
+            if self.reader.is_finished() {
+                return None;
+            }
+            match tokio::time::timeout_at(deadline, received.changed()).await {
+                Ok(Ok(())) => {}
+                Ok(Err(_)) | Err(_) => return check(&self.events.lock().unwrap()),
+            }
+        }
+    }
+
+    /// Send a command on the agent channel and wait for its response.
+    pub async fn command(&self, session_id: &str, payload: CommandPayload) -> Result<Value> {
+        let id = uuid::Uuid::new_v4().to_string();
+        let command = Command {
+            id: Some(id.clone()),
+            session_id: session_id.to_string(),
+            runner_id: None,
+            payload,
+        };
+        let mut message = serde_json::to_value(&command)?;
+        message["channel"] = Value::String("agent".to_string());
+        self.sink
+            .lock()
+            .await
+            .send(Message::text(message.to_string()))
+            .await
+            .context("sending command")?;
+
+        let response = self
+            .wait_for(COMMAND_TIMEOUT, |events| {
+                events
+                    .iter()
+                    .find(|event| {
+                        event.get("event").and_then(Value::as_str) == Some("response")
+                            && event.get("id").and_then(Value::as_str) == Some(id.as_str())
+                    })
+                    .cloned()
+            })
+            .await
+            .ok_or_else(|| anyhow!("no response within {}s", COMMAND_TIMEOUT.as_secs()))?;
+        if response.get("success").and_then(Value::as_bool) != Some(true) {
+            bail!(
+                "command failed: {}",
+                response
+                    .get("error")
+                    .and_then(Value::as_str)
+                    .unwrap_or("no error message")
+            );
+        }
+        Ok(response)
+    }
+}
+
+impl Drop for EventStream {
+    fn drop(&mut self) {
+        self.reader.abort();
+    }
+}
@@ -0,0 +1,345 @@
+//! Runs scenario steps against the API.
+
+use std::collections::HashMap;
+use std::time::Duration;
+
+use anyhow::{Context, Result, anyhow, bail};
+use oqto_protocol::commands::{CommandPayload, SessionConfig};
+use reqwest::{Method, StatusCode};
+use serde::Deserialize;
+use serde_json::{Value, json};
+
+use crate::client::{Api, EventStream};
+use crate::scenario::{
+    CreateSession, ExpectEvents, ExportHistory, Register, Request, Scenario, Step, match_sequence,
+    substitute_value,
+};
+
+/// Events shown when an expectation fails.
+const FAILURE_EVENT_TAIL: usize = 20;
+
+/// Interval between history polls.
+const HISTORY_POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// State of one scenario run.
+pub struct Execution {
+    api: Api,
+    vars: HashMap<String, String>,
+    stream: Option<EventStream>,
+    /// Events of the session before this position were consumed by earlier
+    /// expectations.
+    cursor: usize,
+}
+
+impl Execution {
+    pub fn new(base_url: &str, run_id: &str, scenario: &Scenario) -> Self {
+        let mut vars = scenario.vars.clone();
+        vars.insert("run".to_string(), run_id.to_string());
+        vars.insert("base_url".to_string(), base_url.to_string());
+        Self {
+            api: Api::new(base_url),
+            vars,
+            stream: None,
+            cursor: 0,
+        }
+    }
+
+    /// Run every step, stopping at the first failure.
+    pub async fn run(&mut self, scenario: &Scenario) -> Result<()> {
+        for (index, raw) in scenario.steps.iter().enumerate() {
+            let step = substitute_value(raw, &self.vars)
+                .and_then(|value| Ok(Step::deserialize(&value)?))
+                .with_context(|| format!("step {}", index + 1))?;
+            log::debug!("{}: step {} ({})", scenario.name, index + 1, step.kind());
+            self.step(&step)
+                .await
+                .with_context(|| format!("step {} ({})", index + 1, step.kind()))?;
+        }
+        Ok(())
+    }
+
+    async fn step(&mut self, step: &Step) -> Result<()> {
+        match step {
+            Step::Register(register) => self.register(register).await,
+            Step::Login(login) => {
+                let body = json!({ "username": login.username, "password": login.password });
+                self.authenticate("/api/auth/login", &body).await
+            }
+            Step::Request(request) => self.request(request).await,
+            Step::CreateSession(create) => self.create_session(create).await,
+            Step::Prompt(prompt) => {
+                let payload = CommandPayload::Prompt {
+                    message: prompt.message.clone(),
+                    images: None,
+                    client_id: None,
+                };
+                self.session_command(payload).await
+            }
+            Step::ExpectEvents(expect) => self.expect_events(expect).await,
+            Step::ApproveTool(approve) => {
+                let path = format!(
+                    "/api/sessions/{}/approvals/{}",
+                    self.var("session_id")?,
+                    approve.approval_id
+                );
+                let body = json!({ "approved": approve.approved, "reason": approve.reason });
+                let (status, response) = self.api.request(Method::POST, &path, Some(&body)).await?;
+                expect_status(status, None, &response)
+            }
+            Step::Abort {} => self.session_command(CommandPayload::Abort).await,
+            Step::ExportHistory(export) => self.export_history(export).await,
+        }
+    }
+
+    fn var(&self, name: &str) -> Result<&str> {
+        self.vars
+            .get(name)
+            .map(String::as_str)
+            .ok_or_else(|| anyhow!("'{name}' is not set yet"))
+    }
+
+    async fn register(&mut self, register: &Register) -> Result<()> {
+        let body = json!({
+            "username": register.username,
+            "email": register.email,
+            "password": register.password,
+            "invite_code": register.invite_code,
+            "display_name": register.display_name,
+        });
+        self.authenticate("/api/auth/register", &body).await
+    }
+
+    /// Log in or register and act as the user from now on.
+    async fn authenticate(&mut self, path: &str, body: &Value) -> Result<()> {
+        let (status, response) = self.api.request(Method::POST, path, Some(body)).await?;
+        expect_status(status, None, &response)?;
+        let token = response
+            .get("token")
+            .and_then(Value::as_str)
+            .ok_or_else(|| anyhow!("no token in response: {response}"))?;
+        self.api.set_token(token.to_string());
+        if let Some(user_id) = response.pointer("/user/id").and_then(Value::as_str) {
+            self.vars.insert("user_id".to_string(), user_id.to_string());
+        }
+        // A socket of the previous user would report their events.
+        self.stream = None;
+        self.cursor = 0;
+        Ok(())
+    }
+
+    async fn request(&mut self, request: &Request) -> Result<()> {
+        let method = Method::from_bytes(request.method.to_uppercase().as_bytes())
+            .with_context(|| format!("invalid method '{}'", request.method))?;
+        let (status, response) = self
+            .api
+            .request(method, &request.path, request.body.as_ref())
+            .await?;
+        expect_status(status, request.status, &response)?;
+        for (name, pointer) in &request.save {
+            let value = response
+                .pointer(pointer)
+                .ok_or_else(|| anyhow!("{pointer} is not in the response: {response}"))?;
+            let value = match value {
+                Value::String(s) => s.clone(),
+                other => other.to_string(),
+            };
+            self.vars.insert(name.clone(), value);
+        }
+        Ok(())
+    }
+
+    async fn stream(&mut self) -> Result<&EventStream> {
+        if self.stream.is_none() {
+            self.stream = Some(self.api.connect().await?);
+        }
+        Ok(self.stream.as_ref().expect("connected above"))
+    }
+
+    async fn create_session(&mut self, create: &CreateSession) -> Result<()> {
+        let session_id = format!("oqto-{}", uuid::Uuid::new_v4());
+        let payload = CommandPayload::SessionCreate {
+            config: SessionConfig {
+                harness: create.harness.clone(),
+                cwd: create.cwd.clone(),
+                provider: create.provider.clone(),
+                model: create.model.clone(),
+                continue_session: None,
+                sandbox_profile: None,
+            },
+            last_seen_seq: None,
+        };
+        self.stream().await?.command(&session_id, payload).await?;
+        self.vars.insert("session_id".to_string(), session_id);
+        self.cursor = 0;
+        Ok(())
+    }
+
+    async fn session_command(&mut self, payload: CommandPayload) -> Result<()> {
+        let session_id = self.var("session_id")?.to_string();
+        self.stream().await?.command(&session_id, payload).await?;
+        Ok(())
+    }
+
+    async fn expect_events(&mut self, expect: &ExpectEvents) -> Result<()> {
+        let session_id = self.var("session_id")?.to_string();
+        let cursor = self.cursor;
+        let stream = self.stream().await?;
+        let timeout = Duration::from_secs(expect.timeout_secs);
+        let matched = stream
+            .wait_for(timeout, |events| {
+                let received = session_events(events, &session_id);
+                let received = &received[cursor.min(received.len())..];
+                let end = match_sequence(received, &expect.events);
+                // A forbidden event fails the step as soon as it arrives.
+                let forbidden = received
+                    .iter()
+                    .take(end.unwrap_or(received.len()))
+                    .find(|event| {
+                        event_name(event)
+                            .is_some_and(|name| expect.forbid.iter().any(|f| f == name))
+                    });
+                match (forbidden, end) {
+                    (Some(event), _) => Some(Err(event.clone())),
+                    (None, Some(end)) => Some(Ok(end)),
+                    (None, None) => None,
+                }
+            })
+            .await;
+        let received = session_events(&stream.events(), &session_id);
+        let received = &received[cursor.min(received.len())..];
+
+        match matched {
+            Some(Ok(end)) => {
+                self.remember(&received[..end]);
+                self.cursor = cursor + end;
+                Ok(())
+            }
+            Some(Err(event)) => bail!("forbidden event arrived: {event}"),
+            None => {
+                let expected = expect
+                    .events
+                    .iter()
+                    .map(|matcher| matcher.event())
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                bail!(
+                    "expected [{expected}] within {}s; last events of the session:\n{}",
+                    expect.timeout_secs,
+                    tail(received)
+                );
+            }
+        }
+    }
+
+    /// Keep IDs later steps refer to.
+    fn remember(&mut self, events: &[Value]) {
+        for event in events {
+            if event_name(event) == Some("tool.approval_required")
+                && let Some(approval_id) = event.get("approval_id").and_then(Value::as_str)
+            {
+                self.vars
+                    .insert("approval_id".to_string(), approval_id.to_string());
+            }
+        }
+    }
+
+    async fn export_history(&mut self, export: &ExportHistory) -> Result<()> {
+        let path = format!("/api/chat-history/{}/messages", self.var("session_id")?);
+        let deadline = tokio::time::Instant::now() + Duration::from_secs(export.timeout_secs);
+        loop {
+            let (status, response) = self.api.request(Method::GET, &path, None).await?;
+            let text = response.to_string();
+            let missing: Vec<&String> = export
+                .contains
+                .iter()
+                .filter(|needle| !text.contains(needle.as_str()))
+                .collect();
+            if status.is_success() && missing.is_empty() {
+                return Ok(());
+            }
+            if tokio::time::Instant::now() >= deadline {
+                expect_status(status, None, &response)?;
+                bail!("history does not contain {missing:?}: {text}");
+            }
+            tokio::time::sleep(HISTORY_POLL_INTERVAL).await;
+        }
+    }
+}
+
+/// Fail unless `status` is `expected` (any 2xx when unset).
+fn expect_status(status: StatusCode, expected: Option<u16>, body: &Value) -> Result<()> {
+    let ok = match expected {
+        Some(expected) => status.as_u16() == expected,
+        None => status.is_success(),
+    };
+    if !ok {
+        let expected = expected.map_or_else(|| "2xx".to_string(), |s| s.to_string());
+        bail!("expected status {expected}, got {status}: {body}");
+    }
+    Ok(())
+}
+
+fn event_name(event: &Value) -> Option<&str> {
+    event.get("event").and_then(Value::as_str)
+}
+
+/// Events of one session.
+fn session_events(events: &[Value], session_id: &str) -> Vec<Value> {
+    events
+        .iter()
+        .filter(|event| event.get("session_id").and_then(Value::as_str) == Some(session_id))
+        .cloned()
+        .collect()
+}
+
+/// The last events as one line each, for failure messages.
+fn tail(events: &[Value]) -> String {
+    let start = events.len().saturating_sub(FAILURE_EVENT_TAIL);
+    if events.is_empty() {
+        return "  (none)".to_string();
+    }
+    events[start..]
+        .iter()
+        .map(|event| {
+            let mut line = event.to_string();
+            if line.len() > 200 {
+                let cut = (0..=200)
+                    .rev()
+                    .find(|&i| line.is_char_boundary(i))
+                    .unwrap_or(0);
+                line.truncate(cut);
+                line.push('…');
+            }
+            format!("  {line}")
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn checks_statuses() {
+        let body = json!({"error": "nope"});
+        assert!(expect_status(StatusCode::CREATED, None, &body).is_ok());
+        assert!(expect_status(StatusCode::FORBIDDEN, Some(403), &body).is_ok());
+        let err = expect_status(StatusCode::FORBIDDEN, None, &body).unwrap_err();
+        assert!(err.to_string().contains("expected status 2xx"));
+        assert!(expect_status(StatusCode::OK, Some(201), &body).is_err());
+    }
+
+    #[test]
+    fn filters_session_events() {
+        let events = vec![
+            json!({"session_id": "a", "event": "agent.working"}),
+            json!({"session_id": "b", "event": "agent.idle"}),
+            json!({"session_id": "a", "event": "agent.idle"}),
+        ];
+        let a = session_events(&events, "a");
+        assert_eq!(a.len(), 2);
+        assert_eq!(event_name(&a[1]), Some("agent.idle"));
+        assert_eq!(tail(&[]), "  (none)");
+    }
+}
@@ -0,0 +1,590 @@
+//! Fake agent harness.
+//!
+//! Speaks enough of Pi's RPC protocol (JSON commands on stdin, events and
+//! responses on stdout) for the runner to drive a session without a model.
+//! A prompt is streamed back as the assistant's answer, unless it starts
+//! with a directive that scripts the turn (see [`Directive`]).
+//!
+//! When the runner loads the tool approval bridge (`--extension`), tool
+//! calls ask it first with the same `input` UI request the bridge sends.
+
+use std::collections::VecDeque;
+use std::io::Write;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use anyhow::{Context, Result};
+use oqto_pi::{
+    AgentMessage, AssistantMessageEvent, ContentBlock, ExtensionUiRequest, PiEvent, ToolResult,
+};
+use serde::Serialize;
+use serde_json::{Value, json};
+use tokio::io::{AsyncBufReadExt, BufReader};
+use tokio::sync::mpsc;
+use tokio::time::Instant;
+
+/// Title of the bridge extension's UI requests (see the runner's
+/// `tool_approval` module).
+const APPROVAL_REQUEST_TITLE: &str = "oqto_approval";
+
+/// Characters per streamed text delta.
+const DELTA_CHARS: usize = 8;
+
+/// What a prompt makes the fake agent do.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Directive {
+    /// Answer with the prompt.
+    Echo(String),
+    /// `tool:<name> <command>`: call a tool with `{"command": ...}`.
+    Tool { name: String, command: String },
+    /// `sleep:<secs>`: work for a while.
+    Sleep(Duration),
+    /// `fail:<message>`: end the turn with an error.
+    Fail(String),
+}
+
+impl Directive {
+    pub fn parse(prompt: &str) -> Self {
+        let prompt = prompt.trim();
+        if let Some(call) = prompt.strip_prefix("tool:") {
+            let (name, command) = call.split_once(' ').unwrap_or((call, ""));
+            return Self::Tool {
+                name: name.to_string(),
+                command: command.trim().to_string(),
+            };
+        }
+        if let Some(secs) = prompt.strip_prefix("sleep:")
+            && let Ok(secs) = secs.trim().parse::<f64>()
+        {
+            return Self::Sleep(Duration::from_secs_f64(secs.max(0.0)));
+        }
+        if let Some(message) = prompt.strip_prefix("fail:") {
+            return Self::Fail(message.trim().to_string());
+        }
+        Self::Echo(prompt.to_string())
+    }
+}
+
+/// Options of `oqto-e2e fake-agent`.
+#[derive(Debug, Clone, Default)]
+pub struct FakeAgentOptions {
+    pub session_id: Option<String>,
+    pub session_file: Option<String>,
+    /// Whether the approval bridge extension was loaded.
+    pub approvals: bool,
+}
+
+/// How waiting inside a turn ended.
+enum Wait {
+    Elapsed,
+    Aborted,
+    UiResponse(Value),
+    Closed,
+}
+
+struct FakeAgent {
+    options: FakeAgentOptions,
+    messages: Vec<AgentMessage>,
+    /// Prompts that arrived during a turn, run after it.
+    queued: VecDeque<String>,
+    streaming: bool,
+    next_id: u64,
+}
+
+/// Run the fake agent until stdin closes.
+pub async fn run(options: FakeAgentOptions) -> Result<()> {
+    let (tx, mut rx) = mpsc::unbounded_channel();
+    tokio::spawn(async move {
+        let mut lines = BufReader::new(tokio::io::stdin()).lines();
+        while let Ok(Some(line)) = lines.next_line().await {
+            match serde_json::from_str::<Value>(&line) {
+                Ok(command) => {
+                    if tx.send(command).is_err() {
+                        break;
+                    }
+                }
+                Err(e) => log::warn!("Ignoring malformed command: {}", e),
+            }
+        }
+    });
+
+    let mut agent = FakeAgent {
+        options,
+        messages: Vec::new(),
+        queued: VecDeque::new(),
+        streaming: false,
+        next_id: 0,
+    };
+    while let Some(command) = rx.recv().await {
+        if let Some(prompt) = agent.handle(&command)? {
+            agent.queued.push_back(prompt);
+        }
+        while let Some(prompt) = agent.queued.pop_front() {
+            if !agent.turn(&prompt, &mut rx).await? {
+                return Ok(());
+            }
+        }
+    }
+    Ok(())
+}
+
+impl FakeAgent {
+    /// Answer a command. Returns the message of prompts.
+    fn handle(&mut self, command: &Value) -> Result<Option<String>> {
+        let kind = command.get("type").and_then(Value::as_str).unwrap_or("");
+        let data = match kind {
+            "prompt" | "steer" | "follow_up" => {
+                self.respond(command, None)?;
+                let message = command.get("message").and_then(Value::as_str);
+                return Ok(Some(message.unwrap_or_default().to_string()));
+            }
+            // The UI request was answered after the turn gave up on it.
+            "extension_ui_response" => return Ok(None),
+            "get_state" => Some(self.state()),
+            "get_messages" => Some(json!({ "messages": self.messages })),
+            "get_available_models" => Some(json!({ "models": [] })),
+            "get_last_assistant_text" => Some(json!({ "text": self.last_assistant_text() })),
+            "get_commands" => Some(json!({ "commands": [] })),
+            _ => None,
+        };
+        self.respond(command, data)?;
+        Ok(None)
+    }
+
+    fn respond(&self, command: &Value, data: Option<Value>) -> Result<()> {
+        let mut response = json!({
+            "type": "response",
+            "command": command.get("type").cloned().unwrap_or(Value::Null),
+            "success": true,
+        });
+        if let Some(id) = command.get("id") {
+            response["id"] = id.clone();
+        }
+        if let Some(data) = data {
+            response["data"] = data;
+        }
+        emit(&response)
+    }
+
+    fn state(&self) -> Value {
+        json!({
+            "model": null,
+            "thinkingLevel": "off",
+            "isStreaming": self.streaming,
+            "isCompacting": false,
+            "steeringMode": "all",
+            "followUpMode": "all",
+            "sessionFile": self.options.session_file,
+            "sessionId": self.options.session_id,
+            "sessionName": null,
+            "autoCompactionEnabled": false,
+            "messageCount": self.messages.len(),
+            "pendingMessageCount": self.queued.len(),
+        })
+    }
+
+    fn last_assistant_text(&self) -> Option<String> {
+        self.messages
+            .iter()
+            .rev()
+            .find(|message| message.role == "assistant")
+            .and_then(|message| message.content.as_array())
+            .and_then(|content| {
+                content
+                    .iter()
+                    .find_map(|block| block.get("text").and_then(Value::as_str))
+            })
+            .map(str::to_string)
+    }
+
+    fn id(&mut self, prefix: &str) -> String {
+        self.next_id += 1;
+        format!("{prefix}_{}", self.next_id)
+    }
+
+    /// Run one turn. Returns false when stdin closed during it.
+    async fn turn(
+        &mut self,
+        prompt: &str,
+        rx: &mut mpsc::UnboundedReceiver<Value>,
+    ) -> Result<bool> {
+        self.streaming = true;
+        emit(&PiEvent::AgentStart)?;
+        emit(&PiEvent::TurnStart)?;
+        let first = self.messages.len();
+        let user = message("user", json!([{ "type": "text", "text": prompt }]));
+        emit(&PiEvent::MessageStart {
+            message: user.clone(),
+        })?;
+        emit(&PiEvent::MessageEnd {
+            message: user.clone(),
+        })?;
+        self.messages.push(user);
+
+        let open = match Directive::parse(prompt) {
+            Directive::Echo(text) => {
+                self.answer(&text, "stop")?;
+                true
+            }
+            Directive::Tool { name, command } => self.tool_call(&name, &command, rx).await?,
+            Directive::Sleep(duration) => {
+                emit(&PiEvent::MessageStart {
+                    message: message("assistant", json!([])),
+                })?;
+                match self.wait(rx, Some(Instant::now() + duration), None).await? {
+                    Wait::Aborted => {
+                        self.end_answer("", "aborted")?;
+                        true
+                    }
+                    Wait::Closed => false,
+                    Wait::Elapsed | Wait::UiResponse(_) => {
+                        self.end_answer(&format!("Slept {}s.", duration.as_secs_f64()), "stop")?;
+                        true
+                    }
+                }
+            }
+            Directive::Fail(error) => {
+                let mut failed = message("assistant", json!([]));
+                failed.stop_reason = Some("error".to_string());
+                failed
+                    .extra
+                    .insert("errorMessage".to_string(), json!(error));
+                emit(&PiEvent::MessageStart {
+                    message: failed.clone(),
+                })?;
+                emit(&PiEvent::MessageEnd {
+                    message: failed.clone(),
+                })?;
+                self.messages.push(failed);
+                true
+            }
+        };
+
+        let turn = self.messages[first..].to_vec();
+        if let Some(last) = turn.last() {
+            emit(&PiEvent::TurnEnd {
+                message: last.clone(),
+                tool_results: Vec::new(),
+            })?;
+        }
+        emit(&PiEvent::AgentEnd { messages: turn })?;
+        self.streaming = false;
+        Ok(open)
+    }
+
+    /// Stream `text` as a complete assistant message.
+    fn answer(&mut self, text: &str, stop_reason: &str) -> Result<()> {
+        emit(&PiEvent::MessageStart {
+            message: message("assistant", json!([])),
+        })?;
+        self.end_answer(text, stop_reason)
+    }
+
+    /// Stream `text` into the open assistant message and end it.
+    fn end_answer(&mut self, text: &str, stop_reason: &str) -> Result<()> {
+        let chars: Vec<char> = text.chars().collect();
+        let mut streamed = String::new();
+        if !chars.is_empty() {
+            let partial = message("assistant", json!([{ "type": "text", "text": "" }]));
+            emit(&update(
+                &partial,
+                AssistantMessageEvent::TextStart {
+                    content_index: 0,
+                    partial: json!(partial),
+                },
+            ))?;
+            for chunk in chars.chunks(DELTA_CHARS) {
+                let delta: String = chunk.iter().collect();
+                streamed.push_str(&delta);
+                let partial = message("assistant", json!([{ "type": "text", "text": streamed }]));
+                emit(&update(
+                    &partial,
+                    AssistantMessageEvent::TextDelta {
+                        content_index: 0,
+                        delta,
+                        partial: json!(partial),
+                    },
+                ))?;
+            }
+            let partial = message("assistant", json!([{ "type": "text", "text": text }]));
+            emit(&update(
+                &partial,
+                AssistantMessageEvent::TextEnd {
+                    content_index: 0,
+                    content: text.to_string(),
+                    partial: json!(partial),
+                },
+            ))?;
+        }
+        let content = if text.is_empty() {
+            json!([])
+        } else {
+            json!([{ "type": "text", "text": text }])
+        };
+        let mut done = message("assistant", content);
+        done.stop_reason = Some(stop_reason.to_string());
+        emit(&PiEvent::MessageEnd {
+            message: done.clone(),
+        })?;
+        self.messages.push(done);
+        Ok(())
+    }
+
+    /// Call a tool, asking the approval bridge first when it is loaded.
+    /// Returns false when stdin closed during it.
+    async fn tool_call(
+        &mut self,
+        name: &str,
+        command: &str,
+        rx: &mut mpsc::UnboundedReceiver<Value>,
+    ) -> Result<bool> {
+        let tool_call_id = self.id("call");
+        let args = json!({ "command": command });
+        let mut call = message(
+            "assistant",
+            json!([{ "type": "toolCall", "id": tool_call_id, "name": name, "arguments": args }]),
+        );
+        call.stop_reason = Some("toolUse".to_string());
+        emit(&PiEvent::MessageStart {
+            message: call.clone(),
+        })?;
+        emit(&PiEvent::MessageEnd {
+            message: call.clone(),
+        })?;
+        self.messages.push(call);
+
+        let mut denial = None;
+        if self.options.approvals {
+            let request_id = self.id("ui");
+            emit(&PiEvent::ExtensionUiRequest(approval_request(
+                &request_id,
+                &tool_call_id,
+                name,
+                &args,
+            )))?;
+            denial = match self.wait(rx, None, Some(&request_id)).await? {
+                Wait::UiResponse(response) => approval_denial(&response),
+                Wait::Aborted => {
+                    self.end_answer("", "aborted")?;
+                    return Ok(true);
+                }
+                Wait::Closed | Wait::Elapsed => return Ok(false),
+            };
+        }
+
+        let (output, is_error) = match &denial {
+            None => {
+                emit(&PiEvent::ToolExecutionStart {
+                    tool_call_id: tool_call_id.clone(),
+                    tool_name: name.to_string(),
+                    args: args.clone(),
+                })?;
+                (format!("ran: {command}"), false)
+            }
+            Some(reason) => (format!("Blocked: {reason}"), true),
+        };
+        emit(&PiEvent::ToolExecutionEnd {
+            tool_call_id: tool_call_id.clone(),
+            tool_name: name.to_string(),
+            result: ToolResult {
+                content: vec![ContentBlock::Text {
+                    text: output.clone(),
+                }],
+                details: None,
+            },
+            is_error,
+        })?;
+        let mut result = message("toolResult", json!([{ "type": "text", "text": output }]));
+        result.tool_call_id = Some(tool_call_id);
+        result.tool_name = Some(name.to_string());
+        result.is_error = Some(is_error);
+        emit(&PiEvent::MessageStart {
+            message: result.clone(),
+        })?;
+        emit(&PiEvent::MessageEnd {
+            message: result.clone(),
+        })?;
+        self.messages.push(result);
+
+        let answer = match denial {
+            None => format!("Ran `{command}`."),
+            Some(_) => format!("`{command}` was not allowed."),
+        };
+        self.answer(&answer, "stop")?;
+        Ok(true)
+    }
+
+    /// Serve commands until `deadline`, an abort, or the UI response to
+    /// `ui_request`.
+    async fn wait(
+        &mut self,
+        rx: &mut mpsc::UnboundedReceiver<Value>,
+        deadline: Option<Instant>,
+        ui_request: Option<&str>,
+    ) -> Result<Wait> {
+        loop {
+            let command = match deadline {
+                Some(deadline) => match tokio::time::timeout_at(deadline, rx.recv()).await {
+                    Ok(command) => command,
+                    Err(_) => return Ok(Wait::Elapsed),
+                },
+                None => rx.recv().await,
+            };
+            let Some(command) = command else {
+                return Ok(Wait::Closed);
+            };
+            let kind = command.get("type").and_then(Value::as_str).unwrap_or("");
+            let answers_request = kind == "extension_ui_response"
+                && command.get("id").and_then(Value::as_str) == ui_request;
+            match kind {
+                "abort" => {
+                    self.respond(&command, None)?;
+                    return Ok(Wait::Aborted);
+                }
+                _ if answers_request => return Ok(Wait::UiResponse(command)),
+                _ => {
+                    if let Some(prompt) = self.handle(&command)? {
+                        self.queued.push_back(prompt);
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// The UI request the approval bridge sends before a tool call.
+fn approval_request(id: &str, tool_call_id: &str, name: &str, args: &Value) -> ExtensionUiRequest {
+    let call = json!({ "toolCallId": tool_call_id, "toolName": name, "input": args });
+    ExtensionUiRequest {
+        id: id.to_string(),
+        method: "input".to_string(),
+        title: Some(APPROVAL_REQUEST_TITLE.to_string()),
+        message: None,
+        options: None,
+        timeout: None,
+        status_key: None,
+        status_text: None,
+        widget_key: None,
+        widget_lines: None,
+        widget_placement: None,
+        text: None,
+        prefill: None,
+        placeholder: Some(call.to_string()),
+        notify_type: None,
+    }
+}
+
+/// Reason a tool call was denied, from the runner's answer to the approval
+/// request (`{"approved": bool, "reason": ...}` as the value). None when
+/// it was approved.
+fn approval_denial(response: &Value) -> Option<String> {
+    if response.get("cancelled").and_then(Value::as_bool) == Some(true) {
+        return Some("cancelled".to_string());
+    }
+    let decision: Value = response
+        .get("value")
+        .and_then(Value::as_str)
+        .and_then(|value| serde_json::from_str(value).ok())
+        .unwrap_or_default();
+    if decision.get("approved").and_then(Value::as_bool) == Some(true) {
+        return None;
+    }
+    Some(
+        decision
+            .get("reason")
+            .and_then(Value::as_str)
+            .unwrap_or("denied")
+            .to_string(),
+    )
+}
+
+fn message(role: &str, content: Value) -> AgentMessage {
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .ok();
+    let assistant = role == "assistant";
+    AgentMessage {
+        role: role.to_string(),
+        content,
+        timestamp,
+        tool_call_id: None,
+        tool_name: None,
+        is_error: None,
+        api: assistant.then(|| "fake".to_string()),
+        provider: assistant.then(|| "fake".to_string()),
+        model: assistant.then(|| "fake".to_string()),
+        usage: None,
+        stop_reason: None,
+        extra: Default::default(),
+    }
+}
+
+fn update(partial: &AgentMessage, event: AssistantMessageEvent) -> PiEvent {
+    PiEvent::MessageUpdate {
+        message: partial.clone(),
+        assistant_message_event: Box::new(event),
+    }
+}
+
+/// Write one JSON line to stdout.
+fn emit(value: &impl Serialize) -> Result<()> {
+    let line = serde_json::to_string(value)?;
+    let mut stdout = std::io::stdout().lock();
+    writeln!(stdout, "{line}")
+        .and_then(|()| stdout.flush())
+        .context("writing to stdout")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_directives() {
+        assert_eq!(
+            Directive::parse("tool:bash git push origin main"),
+            Directive::Tool {
+                name: "bash".into(),
+                command: "git push origin main".into()
+            }
+        );
+        assert_eq!(
+            Directive::parse("sleep:1.5"),
+            Directive::Sleep(Duration::from_millis(1500))
+        );
+        assert_eq!(
+            Directive::parse("fail: boom"),
+            Directive::Fail("boom".into())
+        );
+        assert_eq!(
+            Directive::parse("sleep:later"),
+            Directive::Echo("sleep:later".into())
+        );
+        assert_eq!(Directive::parse(" hello "), Directive::Echo("hello".into()));
+    }
+
+    #[test]
+    fn reads_approval_decisions() {
+        let approved = json!({"type": "extension_ui_response", "id": "ui_1",
+            "value": r#"{"approved":true,"reason":null}"#});
+        assert_eq!(approval_denial(&approved), None);
+
+        let denied = json!({"type": "extension_ui_response", "id": "ui_1",
+            "value": r#"{"approved":false,"reason":"not on main"}"#});
+        assert_eq!(approval_denial(&denied).as_deref(), Some("not on main"));
+
+        let cancelled = json!({"type": "extension_ui_response", "id": "ui_1", "cancelled": true});
+        assert_eq!(approval_denial(&cancelled).as_deref(), Some("cancelled"));
+    }
+
+    #[test]
+    fn approval_requests_match_the_bridge() {
+        let request = approval_request("ui_1", "call_1", "bash", &json!({"command": "ls"}));
+        assert_eq!(request.method, "input");
+        assert_eq!(request.title.as_deref(), Some(APPROVAL_REQUEST_TITLE));
+        let call: Value = serde_json::from_str(request.placeholder.as_deref().unwrap()).unwrap();
+        assert_eq!(call["toolCallId"], "call_1");
+        assert_eq!(call["toolName"], "bash");
+        assert_eq!(call["input"]["command"], "ls");
+    }
+}
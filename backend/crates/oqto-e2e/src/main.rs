@@ -0,0 +1,151 @@
+//! oqto-e2e - End-to-end scenario runner for Oqto
+//!
+//! Starts a backend and runner with the fake agent harness, runs YAML
+//! scenarios over the real HTTP and WebSocket APIs and asserts on the
+//! canonical events they produce. The same binary is the fake agent
+//! (`oqto-e2e fake-agent`) the runner spawns for sessions.
+
+mod client;
+mod executor;
+mod fake_agent;
+mod scenario;
+mod stack;
+
+use std::path::PathBuf;
+use std::process::ExitCode;
+use std::time::Instant;
+
+use anyhow::{Result, bail};
+use clap::{Parser, Subcommand};
+
+use crate::executor::Execution;
+use crate::fake_agent::FakeAgentOptions;
+use crate::scenario::Scenario;
+use crate::stack::{Stack, StackConfig};
+
+/// File name of the runner's tool approval bridge extension.
+const APPROVAL_BRIDGE: &str = "oqto-approvals.ts";
+
+#[derive(Parser, Debug)]
+#[command(name = "oqto-e2e", about = "End-to-end scenario runner for Oqto")]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Run scenarios
+    Run {
+        /// Scenario files (YAML)
+        #[arg(required = true)]
+        scenarios: Vec<PathBuf>,
+        /// Stack to start before the scenarios run
+        #[arg(long, value_name = "PATH", conflicts_with = "base_url")]
+        stack: Option<PathBuf>,
+        /// Run against a stack that is already up
+        #[arg(long, value_name = "URL")]
+        base_url: Option<String>,
+        /// Keep the stack's work directory (configs, logs) after the run
+        #[arg(long)]
+        keep_work_dir: bool,
+    },
+    /// Fake agent harness speaking Pi's RPC protocol (spawned by the runner)
+    FakeAgent {
+        #[arg(long)]
+        session_id: Option<String>,
+        #[arg(long)]
+        session_file: Option<String>,
+        /// Extensions to load; only the tool approval bridge is understood
+        #[arg(long = "extension", value_name = "PATH")]
+        extensions: Vec<PathBuf>,
+    },
+}
+
+fn main() -> ExitCode {
+    let cli = Cli::parse();
+    env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("warn")).init();
+
+    let runtime = match tokio::runtime::Runtime::new() {
+        Ok(runtime) => runtime,
+        Err(e) => {
+            eprintln!("Error: {e}");
+            return ExitCode::FAILURE;
+        }
+    };
+    let result = runtime.block_on(async {
+        match cli.command {
+            Command::Run {
+                scenarios,
+                stack,
+                base_url,
+                keep_work_dir,
+            } => run(scenarios, stack, base_url, keep_work_dir).await,
+            Command::FakeAgent {
+                session_id,
+                session_file,
+                extensions,
+            } => {
+                let options = FakeAgentOptions {
+                    session_id,
+                    session_file,
+                    approvals: extensions
+                        .iter()
+                        .any(|path| path.file_name().is_some_and(|name| name == APPROVAL_BRIDGE)),
+                };
+                fake_agent::run(options).await.map(|()| true)
+            }
+        }
+    });
+    match result {
+        Ok(true) => ExitCode::SUCCESS,
+        Ok(false) => ExitCode::FAILURE,
+        Err(e) => {
+            eprintln!("Error: {e:#}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+/// Run the scenarios. Returns whether all of them passed.
+async fn run(
+    paths: Vec<PathBuf>,
+    stack_file: Option<PathBuf>,
+    base_url: Option<String>,
+    keep_work_dir: bool,
+) -> Result<bool> {
+    let scenarios = paths
+        .iter()
+        .map(|path| Scenario::load(path))
+        .collect::<Result<Vec<_>>>()?;
+
+    let (base_url, _stack) = match (stack_file, base_url) {
+        (Some(stack_file), _) => {
+            let config = StackConfig::load(&stack_file)?;
+            let stack = Stack::start(&config, &stack_file, keep_work_dir).await?;
+            (config.base_url, Some(stack))
+        }
+        (None, Some(base_url)) => (base_url, None),
+        (None, None) => bail!("pass --stack to start a stack or --base-url to use a running one"),
+    };
+
+    let mut failed = 0;
+    for scenario in &scenarios {
+        // Unique per scenario, for names that must not collide (`${run}`).
+        let run_id = uuid::Uuid::new_v4().simple().to_string()[..8].to_string();
+        let started = Instant::now();
+        let result = Execution::new(&base_url, &run_id, scenario)
+            .run(scenario)
+            .await;
+        let elapsed = started.elapsed().as_secs_f64();
+        match result {
+            Ok(()) => println!("PASS {} ({elapsed:.1}s)", scenario.name),
+            Err(e) => {
+                failed += 1;
+                println!("FAIL {} ({elapsed:.1}s): {e:#}", scenario.name);
+            }
+        }
+    }
+    println!("{} passed, {} failed", scenarios.len() - failed, failed);
+    Ok(failed == 0)
+}
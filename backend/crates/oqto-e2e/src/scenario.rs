@@ -0,0 +1,403 @@
+//! Scenario files.
+//!
+//! A scenario is a YAML file with a list of steps run in order against a
+//! live stack:
+//!
+//! ```yaml
+//! name: prompt-echo
+//! vars:
+//!   password: e2e-password-1
+//! steps:
+//!   - register: { username: "e2e-${run}", email: "e2e-${run}@example.com", password: "${password}" }
+//!   - create_session: { harness: fake }
+//!   - prompt: { message: "hello" }
+//!   - expect_events:
+//!       events: [agent.working, stream.text_delta, agent.idle]
+//! ```
+//!
+//! Strings may use `${name}` for scenario variables, variables saved by
+//! earlier steps (`session_id`, `approval_id`, `save:` of a request) and
+//! built-ins (`run`, `base_url`), or `${env.NAME}` for the environment.
+
+use std::collections::HashMap;
+use std::path::Path;
+
+use anyhow::{Context, Result, anyhow, bail};
+use serde::Deserialize;
+use serde_json::{Map, Value};
+
+/// Default time to wait for events or history.
+pub const DEFAULT_TIMEOUT_SECS: u64 = 30;
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Scenario {
+    pub name: String,
+    #[serde(default)]
+    pub description: Option<String>,
+    /// Variables available to every step.
+    #[serde(default)]
+    pub vars: HashMap<String, String>,
+    /// Steps as written. Each is checked against [`Step`] on load and
+    /// parsed again once its variables are substituted.
+    pub steps: Vec<Value>,
+}
+
+impl Scenario {
+    pub fn load(path: &Path) -> Result<Self> {
+        let contents =
+            std::fs::read_to_string(path).with_context(|| format!("reading {}", path.display()))?;
+        Self::parse(&contents).with_context(|| format!("parsing {}", path.display()))
+    }
+
+    pub fn parse(contents: &str) -> Result<Self> {
+        let scenario: Self = serde_yaml::from_str(contents)?;
+        if scenario.steps.is_empty() {
+            bail!("scenario '{}' has no steps", scenario.name);
+        }
+        for (index, step) in scenario.steps.iter().enumerate() {
+            Step::deserialize(step).with_context(|| format!("step {}", index + 1))?;
+        }
+        Ok(scenario)
+    }
+}
+
+/// One step of a scenario.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Step {
+    /// `POST /api/auth/register` and act as the new user.
+    Register(Register),
+    /// `POST /api/auth/login` and act as that user.
+    Login(Login),
+    /// Any HTTP request.
+    Request(Request),
+    /// Create an agent session over the WebSocket (`session.create`) and
+    /// set `session_id`.
+    CreateSession(CreateSession),
+    /// Send a prompt to the session.
+    Prompt(Prompt),
+    /// Wait for events of the session, in order.
+    ExpectEvents(ExpectEvents),
+    /// Approve or deny a paused tool call (by default the last
+    /// `tool.approval_required`).
+    ApproveTool(ApproveTool),
+    /// Abort the session's current turn.
+    Abort {},
+    /// Fetch the session's messages from chat history.
+    ExportHistory(ExportHistory),
+}
+
+impl Step {
+    /// Name of the step as written in scenarios.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Self::Register(_) => "register",
+            Self::Login(_) => "login",
+            Self::Request(_) => "request",
+            Self::CreateSession(_) => "create_session",
+            Self::Prompt(_) => "prompt",
+            Self::ExpectEvents(_) => "expect_events",
+            Self::ApproveTool(_) => "approve_tool",
+            Self::Abort {} => "abort",
+            Self::ExportHistory(_) => "export_history",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Register {
+    pub username: String,
+    pub email: String,
+    pub password: String,
+    #[serde(default)]
+    pub invite_code: Option<String>,
+    #[serde(default)]
+    pub display_name: Option<String>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Login {
+    pub username: String,
+    pub password: String,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Request {
+    #[serde(default = "default_method")]
+    pub method: String,
+    /// Path under the base URL, e.g. `/api/health`.
+    pub path: String,
+    #[serde(default)]
+    pub body: Option<Value>,
+    /// Expected status; any 2xx when unset.
+    #[serde(default)]
+    pub status: Option<u16>,
+    /// Variables to set from the JSON response, by JSON pointer
+    /// (`invite_code: /code`).
+    #[serde(default)]
+    pub save: HashMap<String, String>,
+}
+
+fn default_method() -> String {
+    "GET".to_string()
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CreateSession {
+    #[serde(default = "default_harness")]
+    pub harness: String,
+    #[serde(default)]
+    pub cwd: Option<String>,
+    #[serde(default)]
+    pub provider: Option<String>,
+    #[serde(default)]
+    pub model: Option<String>,
+}
+
+fn default_harness() -> String {
+    oqto_protocol::harness::DEFAULT_HARNESS.to_string()
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Prompt {
+    pub message: String,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ExpectEvents {
+    /// Events that must arrive in this order. Other events may come in
+    /// between.
+    pub events: Vec<EventMatcher>,
+    /// Event names that fail the step when they arrive before the last
+    /// expected event.
+    #[serde(default)]
+    pub forbid: Vec<String>,
+    #[serde(default = "default_timeout")]
+    pub timeout_secs: u64,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ApproveTool {
+    #[serde(default = "default_approved")]
+    pub approved: bool,
+    #[serde(default)]
+    pub reason: Option<String>,
+    #[serde(default = "default_approval_id")]
+    pub approval_id: String,
+}
+
+fn default_approved() -> bool {
+    true
+}
+
+fn default_approval_id() -> String {
+    "${approval_id}".to_string()
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ExportHistory {
+    /// Text the exported messages must contain. Retried until it shows up,
+    /// since history is persisted after the turn ends.
+    #[serde(default)]
+    pub contains: Vec<String>,
+    #[serde(default = "default_timeout")]
+    pub timeout_secs: u64,
+}
+
+fn default_timeout() -> u64 {
+    DEFAULT_TIMEOUT_SECS
+}
+
+/// An expected event: its name (`tool.end`), or its name and fields the
+/// event must have (`{ event: tool.end, fields: { is_error: false } }`).
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(untagged)]
+pub enum EventMatcher {
+    Name(String),
+    Fields {
+        event: String,
+        #[serde(default)]
+        fields: Map<String, Value>,
+    },
+}
+
+impl EventMatcher {
+    pub fn event(&self) -> &str {
+        match self {
+            Self::Name(event) | Self::Fields { event, .. } => event,
+        }
+    }
+
+    pub fn matches(&self, event: &Value) -> bool {
+        if event.get("event").and_then(Value::as_str) != Some(self.event()) {
+            return false;
+        }
+        match self {
+            Self::Name(_) => true,
+            Self::Fields { fields, .. } => fields
+                .iter()
+                .all(|(key, expected)| event.get(key) == Some(expected)),
+        }
+    }
+}
+
+/// Match `expected` in order against `events`. Returns the position after
+/// the last matched event, or None while some are still missing.
+pub fn match_sequence(events: &[Value], expected: &[EventMatcher]) -> Option<usize> {
+    let mut position = 0;
+    for matcher in expected {
+        let offset = events[position..]
+            .iter()
+            .position(|event| matcher.matches(event))?;
+        position += offset + 1;
+    }
+    Some(position)
+}
+
+/// Replace `${name}` and `${env.NAME}` in `input`.
+pub fn substitute(input: &str, vars: &HashMap<String, String>) -> Result<String> {
+    let mut output = String::with_capacity(input.len());
+    let mut rest = input;
+    while let Some(start) = rest.find("${") {
+        output.push_str(&rest[..start]);
+        let end = rest[start..]
+            .find('}')
+            .ok_or_else(|| anyhow!("unterminated variable in '{input}'"))?;
+        let name = &rest[start + 2..start + end];
+        let value = match name.strip_prefix("env.") {
+            Some(env) => {
+                std::env::var(env).map_err(|_| anyhow!("environment variable {env} is not set"))?
+            }
+            None => vars
+                .get(name)
+                .cloned()
+                .ok_or_else(|| anyhow!("variable '{name}' is not set"))?,
+        };
+        output.push_str(&value);
+        rest = &rest[start + end + 1..];
+    }
+    output.push_str(rest);
+    Ok(output)
+}
+
+/// Substitute variables in every string of `value`.
+pub fn substitute_value(value: &Value, vars: &HashMap<String, String>) -> Result<Value> {
+    Ok(match value {
+        Value::String(s) => Value::String(substitute(s, vars)?),
+        Value::Array(items) => Value::Array(
+            items
+                .iter()
+                .map(|item| substitute_value(item, vars))
+                .collect::<Result<_>>()?,
+        ),
+        Value::Object(map) => Value::Object(
+            map.iter()
+                .map(|(key, item)| Ok((key.clone(), substitute_value(item, vars)?)))
+                .collect::<Result<_>>()?,
+        ),
+        other => other.clone(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn parses_scenarios() {
+        let scenario = Scenario::parse(
+            r#"
+name: approve
+steps:
+  - register: { username: "e2e-${run}", email: "e2e@example.com", password: pw }
+  - create_session: { harness: fake }
+  - prompt: { message: "tool:bash git push" }
+  - expect_events:
+      events:
+        - agent.working
+        - { event: tool.approval_required, fields: { name: bash } }
+      forbid: [agent.error]
+  - approve_tool: {}
+  - abort: {}
+  - export_history: { contains: [git push] }
+"#,
+        )
+        .unwrap();
+        assert_eq!(scenario.steps.len(), 7);
+
+        let Step::ExpectEvents(expect) = Step::deserialize(&scenario.steps[3]).unwrap() else {
+            panic!("expected expect_events");
+        };
+        assert_eq!(expect.timeout_secs, DEFAULT_TIMEOUT_SECS);
+        assert_eq!(expect.events[0], EventMatcher::Name("agent.working".into()));
+        assert_eq!(expect.events[1].event(), "tool.approval_required");
+
+        let Step::ApproveTool(approve) = Step::deserialize(&scenario.steps[4]).unwrap() else {
+            panic!("expected approve_tool");
+        };
+        assert!(approve.approved);
+        assert_eq!(approve.approval_id, "${approval_id}");
+
+        let err = Scenario::parse("name: bad\nsteps:\n  - launch: {}\n").unwrap_err();
+        assert!(format!("{err:#}").contains("step 1"));
+    }
+
+    #[test]
+    fn substitutes_variables() {
+        let vars = HashMap::from([
+            ("run".to_string(), "ab12".to_string()),
+            ("session_id".to_string(), "oqto-1".to_string()),
+        ]);
+        assert_eq!(
+            substitute("/api/chat-history/${session_id}", &vars).unwrap(),
+            "/api/chat-history/oqto-1"
+        );
+        assert_eq!(substitute("plain", &vars).unwrap(), "plain");
+        assert!(substitute("${missing}", &vars).is_err());
+        assert!(substitute("${run", &vars).is_err());
+
+        let value = substitute_value(&json!({"user": ["e2e-${run}", 1]}), &vars).unwrap();
+        assert_eq!(value, json!({"user": ["e2e-ab12", 1]}));
+    }
+
+    #[test]
+    fn matches_event_sequences() {
+        let events = vec![
+            json!({"event": "agent.working"}),
+            json!({"event": "stream.text_delta", "delta": "hi"}),
+            json!({"event": "tool.end", "is_error": true}),
+            json!({"event": "agent.idle"}),
+        ];
+        let names = |names: &[&str]| -> Vec<EventMatcher> {
+            names
+                .iter()
+                .map(|name| EventMatcher::Name(name.to_string()))
+                .collect()
+        };
+        assert_eq!(
+            match_sequence(&events, &names(&["agent.working", "agent.idle"])),
+            Some(4)
+        );
+        assert_eq!(
+            match_sequence(&events, &names(&["agent.idle", "agent.working"])),
+            None
+        );
+        assert_eq!(match_sequence(&events, &[]), Some(0));
+
+        let tool_ok = EventMatcher::Fields {
+            event: "tool.end".into(),
+            fields: json!({"is_error": false}).as_object().unwrap().clone(),
+        };
+        assert_eq!(match_sequence(&events, &[tool_ok]), None);
+    }
+}
@@ -0,0 +1,278 @@
+//! The stack under test: processes started before the scenarios run.
+//!
+//! ```toml
+//! base_url = "http://127.0.0.1:18080"
+//! templates = ["config.toml"]
+//!
+//! [[process]]
+//! name = "backend"
+//! command = "${bin_dir}/oqto"
+//! args = ["--config", "${work_dir}/config.toml", "serve", "--local-mode", "--port", "18080"]
+//! ```
+//!
+//! Strings may use `${work_dir}` (a fresh directory per run), `${stack_dir}`
+//! (the directory of the stack file) and `${bin_dir}` (the directory of the
+//! `oqto-e2e` binary, usually next to the other built binaries). Templates
+//! are copied from the stack directory into the work directory with the
+//! same substitutions.
+
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, Stdio};
+use std::time::Duration;
+
+use anyhow::{Context, Result, bail};
+use serde::Deserialize;
+
+use crate::scenario::substitute;
+
+/// Interval between readiness checks.
+const POLL_INTERVAL: Duration = Duration::from_millis(200);
+
+/// Lines of a process log shown when it fails to start.
+const LOG_TAIL_LINES: usize = 20;
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct StackConfig {
+    pub base_url: String,
+    /// Path polled until it answers 200 once the processes are up.
+    #[serde(default = "default_health_path")]
+    pub health_path: String,
+    #[serde(default = "default_startup_timeout")]
+    pub startup_timeout_secs: u64,
+    /// Files (relative to the stack file) rendered into the work directory.
+    #[serde(default)]
+    pub templates: Vec<String>,
+    /// Started in order and stopped in reverse.
+    #[serde(default, rename = "process")]
+    pub processes: Vec<ProcessConfig>,
+}
+
+fn default_health_path() -> String {
+    "/api/health".to_string()
+}
+
+fn default_startup_timeout() -> u64 {
+    60
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ProcessConfig {
+    pub name: String,
+    pub command: String,
+    #[serde(default)]
+    pub args: Vec<String>,
+    #[serde(default)]
+    pub env: HashMap<String, String>,
+    /// Path that exists once the process is ready (e.g. its socket). The
+    /// next process starts after it appears.
+    #[serde(default)]
+    pub wait_for: Option<String>,
+}
+
+impl StackConfig {
+    pub fn load(path: &Path) -> Result<Self> {
+        let contents =
+            fs::read_to_string(path).with_context(|| format!("reading {}", path.display()))?;
+        toml::from_str(&contents).with_context(|| format!("parsing {}", path.display()))
+    }
+}
+
+/// Running stack. Its processes are killed and the work directory removed
+/// when it is dropped.
+pub struct Stack {
+    children: Vec<(String, Child)>,
+    work_dir: PathBuf,
+    keep_work_dir: bool,
+}
+
+impl Stack {
+    /// Start the processes of `config` and wait until the API is healthy.
+    pub async fn start(
+        config: &StackConfig,
+        stack_file: &Path,
+        keep_work_dir: bool,
+    ) -> Result<Self> {
+        let work_dir = std::env::temp_dir().join(format!("oqto-e2e-{}", uuid::Uuid::new_v4()));
+        fs::create_dir_all(work_dir.join("logs"))
+            .with_context(|| format!("creating {}", work_dir.display()))?;
+        let mut stack = Self {
+            children: Vec::new(),
+            work_dir: work_dir.clone(),
+            keep_work_dir,
+        };
+
+        let stack_dir = stack_file
+            .parent()
+            .map(Path::to_path_buf)
+            .unwrap_or_default();
+        let stack_dir = fs::canonicalize(&stack_dir).unwrap_or(stack_dir);
+        let bin_dir = std::env::current_exe()
+            .ok()
+            .and_then(|exe| exe.parent().map(Path::to_path_buf))
+            .unwrap_or_default();
+        let vars = HashMap::from([
+            ("work_dir".to_string(), path_str(&work_dir)),
+            ("stack_dir".to_string(), path_str(&stack_dir)),
+            ("bin_dir".to_string(), path_str(&bin_dir)),
+        ]);
+
+        for template in &config.templates {
+            let source = stack_dir.join(template);
+            let contents = fs::read_to_string(&source)
+                .with_context(|| format!("reading template {}", source.display()))?;
+            let target = work_dir.join(template);
+            if let Some(parent) = target.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            fs::write(&target, substitute(&contents, &vars)?)
+                .with_context(|| format!("writing {}", target.display()))?;
+        }
+
+        let timeout = Duration::from_secs(config.startup_timeout_secs);
+        for process in &config.processes {
+            stack.spawn(process, &vars)?;
+            if let Some(path) = &process.wait_for {
+                let path = &PathBuf::from(substitute(path, &vars)?);
+                stack
+                    .wait_until(&process.name, timeout, || async move { path.exists() })
+                    .await?;
+            }
+        }
+
+        let health_url = format!(
+            "{}{}",
+            config.base_url.trim_end_matches('/'),
+            config.health_path
+        );
+        let http = reqwest::Client::new();
+        let (http, health_url) = (&http, &health_url);
+        stack
+            .wait_until("the API", timeout, || async move {
+                http.get(health_url)
+                    .send()
+                    .await
+                    .is_ok_and(|response| response.status().is_success())
+            })
+            .await?;
+        log::info!("Stack is up (work directory {})", work_dir.display());
+        Ok(stack)
+    }
+
+    fn spawn(&mut self, process: &ProcessConfig, vars: &HashMap<String, String>) -> Result<()> {
+        let log_path = self
+            .work_dir
+            .join("logs")
+            .join(format!("{}.log", process.name));
+        let log =
+            File::create(&log_path).with_context(|| format!("creating {}", log_path.display()))?;
+        let command = substitute(&process.command, vars)?;
+        let mut cmd = Command::new(&command);
+        for arg in &process.args {
+            cmd.arg(substitute(arg, vars)?);
+        }
+        for (key, value) in &process.env {
+            cmd.env(key, substitute(value, vars)?);
+        }
+        cmd.stdin(Stdio::null())
+            .stdout(log.try_clone()?)
+            .stderr(log);
+        let child = cmd
+            .spawn()
+            .with_context(|| format!("starting {} ({command})", process.name))?;
+        log::info!("Started {} (pid {})", process.name, child.id());
+        self.children.push((process.name.clone(), child));
+        Ok(())
+    }
+
+    /// Poll `ready` until it holds, failing early when a process exits.
+    async fn wait_until<F, Fut>(&mut self, what: &str, timeout: Duration, ready: F) -> Result<()>
+    where
+        F: Fn() -> Fut,
+        Fut: std::future::Future<Output = bool>,
+    {
+        let deadline = tokio::time::Instant::now() + timeout;
+        loop {
+            if ready().await {
+                return Ok(());
+            }
+            let exited = self.children.iter_mut().find_map(|(name, child)| {
+                let status = child.try_wait().ok().flatten()?;
+                Some((name.clone(), status))
+            });
+            if let Some((name, status)) = exited {
+                bail!(
+                    "{name} exited ({status}) while waiting for {what}:\n{}",
+                    self.log_tail(&name)
+                );
+            }
+            if tokio::time::Instant::now() >= deadline {
+                bail!("{what} was not ready within {}s", timeout.as_secs());
+            }
+            tokio::time::sleep(POLL_INTERVAL).await;
+        }
+    }
+
+    fn log_tail(&self, name: &str) -> String {
+        let log = fs::read_to_string(self.work_dir.join("logs").join(format!("{name}.log")))
+            .unwrap_or_default();
+        let lines: Vec<&str> = log.lines().collect();
+        lines[lines.len().saturating_sub(LOG_TAIL_LINES)..].join("\n")
+    }
+
+    pub fn work_dir(&self) -> &Path {
+        &self.work_dir
+    }
+}
+
+impl Drop for Stack {
+    fn drop(&mut self) {
+        for (name, child) in self.children.iter_mut().rev() {
+            if let Err(e) = child.kill() {
+                log::debug!("Failed to stop {}: {}", name, e);
+            }
+            let _ = child.wait();
+        }
+        if self.keep_work_dir {
+            log::info!("Kept work directory {}", self.work_dir.display());
+        } else {
+            let _ = fs::remove_dir_all(&self.work_dir);
+        }
+    }
+}
+
+fn path_str(path: &Path) -> String {
+    path.to_string_lossy().into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_stack_config() {
+        let config: StackConfig = toml::from_str(
+            r#"
+base_url = "http://127.0.0.1:18080"
+templates = ["config.toml"]
+
+[[process]]
+name = "runner"
+command = "${bin_dir}/oqto-runner"
+args = ["--config", "${work_dir}/config.toml"]
+wait_for = "${work_dir}/runner.sock"
+
+[process.env]
+XDG_DATA_HOME = "${work_dir}/data"
+"#,
+        )
+        .unwrap();
+        assert_eq!(config.health_path, "/api/health");
+        assert_eq!(config.startup_timeout_secs, 60);
+        assert_eq!(config.processes.len(), 1);
+        assert_eq!(config.processes[0].env["XDG_DATA_HOME"], "${work_dir}/data");
+    }
+}
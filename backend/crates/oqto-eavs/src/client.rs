@@ -256,6 +256,38 @@ impl EavsClient {
         self.handle_response(response).await
     }
 
+    /// Run a chat completion through a provider (`{base}/{provider}/v1`),
+    /// authenticated with the master key and tagged with its lane. Returns
+    /// the first choice's text.
+    pub async fn complete(
+        &self,
+        provider: &str,
+        request: &CompletionRequest,
+        lane: TrafficLane,
+    ) -> EavsResult<String> {
+        let url = format!(
+            "{}/{}/v1/chat/completions",
+            self.base_url.trim_end_matches('/'),
+            provider
+        );
+        let response = self
+            .client
+            .post(&url)
+            .header("Authorization", format!("Bearer {}", self.master_key))
+            .header(TrafficLane::HEADER, lane.as_str())
+            .json(request)
+            .send()
+            .await?;
+
+        let response: CompletionResponse = self.handle_response(response).await?;
+        response
+            .choices
+            .into_iter()
+            .next()
+            .map(|choice| choice.message.content)
+            .ok_or_else(|| EavsError::ParseError("completion has no choices".to_string()))
+    }
+
     /// Handle response and parse JSON or error.
     async fn handle_response<T: serde::de::DeserializeOwned>(
         &self,
@@ -309,4 +341,15 @@ mod tests {
         assert_eq!(perms.max_budget_usd, Some(10.0));
         assert_eq!(perms.rpm_limit, Some(60));
     }
+
+    #[test]
+    fn test_lane_keeps_metadata() {
+        let request = CreateKeyRequest::new("test-session")
+            .metadata(serde_json::json!({"session_id": "abc123"}))
+            .lane(TrafficLane::Interactive);
+        assert_eq!(
+            request.metadata,
+            Some(serde_json::json!({"session_id": "abc123", "lane": "interactive"}))
+        );
+    }
 }
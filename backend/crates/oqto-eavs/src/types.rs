@@ -63,6 +63,41 @@ impl CreateKeyRequest {
         self.oauth_user = Some(user.into());
         self
     }
+
+    /// Tag the key with its traffic lane (`lane` in the metadata). Call
+    /// after [`Self::metadata`], which replaces the metadata.
+    pub fn lane(mut self, lane: TrafficLane) -> Self {
+        let mut metadata = match self.metadata.take() {
+            Some(serde_json::Value::Object(map)) => map,
+            _ => serde_json::Map::new(),
+        };
+        metadata.insert("lane".to_string(), lane.as_str().into());
+        self.metadata = Some(serde_json::Value::Object(metadata));
+        self
+    }
+}
+
+/// Priority lane of LLM traffic, so EAVS can favor interactive chats over
+/// background work sharing the deployment.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TrafficLane {
+    /// A user is waiting for the response.
+    Interactive,
+    /// Scheduled jobs and other work nobody is watching.
+    Background,
+}
+
+impl TrafficLane {
+    /// Header carrying the lane on requests the backend sends itself.
+    pub const HEADER: &'static str = "X-Oqto-Lane";
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Interactive => "interactive",
+            Self::Background => "background",
+        }
+    }
 }
 
 /// Key permissions and limits.
@@ -335,6 +370,52 @@ pub struct OAuthPollResponse {
     pub interval: Option<u64>,
 }
 
+/// One message of a chat completion.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct CompletionMessage {
+    pub role: String,
+    pub content: String,
+}
+
+impl CompletionMessage {
+    pub fn system(content: impl Into<String>) -> Self {
+        Self {
+            role: "system".to_string(),
+            content: content.into(),
+        }
+    }
+
+    pub fn user(content: impl Into<String>) -> Self {
+        Self {
+            role: "user".to_string(),
+            content: content.into(),
+        }
+    }
+}
+
+/// OpenAI-compatible chat completion request, routed through a provider.
+#[derive(Debug, Clone, Serialize)]
+pub struct CompletionRequest {
+    pub model: String,
+    pub messages: Vec<CompletionMessage>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_tokens: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub temperature: Option<f32>,
+}
+
+/// Chat completion response (only the fields oqto reads).
+#[derive(Debug, Clone, Deserialize)]
+pub struct CompletionResponse {
+    #[serde(default)]
+    pub choices: Vec<CompletionChoice>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct CompletionChoice {
+    pub message: CompletionMessage,
+}
+
 /// Error response from EAVS API.
 #[derive(Debug, Clone, Deserialize)]
 pub struct ApiErrorResponse {
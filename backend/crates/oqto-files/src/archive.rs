@@ -0,0 +1,301 @@
+//! Exclusion rules for zip archives of workspace directories.
+//!
+//! Archives skip what the workspace's `.oqtoignore` (or legacy
+//! `.octoignore`) lists plus the admin globs in [`Config::archive_exclude`].
+//! Ignore files use gitignore syntax: `#` comments, `!` to re-include, a
+//! trailing `/` for directories only, and patterns containing a `/` anchored
+//! at the workspace root. Admin globs match at any depth unless they start
+//! with `/`, since workspace layouts differ. The last matching rule wins, and
+//! an excluded directory is skipped as a whole.
+//!
+//! Every archive carries a [`ArchiveManifest`] recording what was left out,
+//! and [`ArchiveEstimate`] is the dry run of the same walk.
+//!
+//! [`Config::archive_exclude`]: crate::Config::archive_exclude
+
+use std::path::Path;
+
+use serde::Serialize;
+use walkdir::{DirEntry, WalkDir};
+
+use crate::handlers::get_relative_path;
+
+/// Ignore files read from the workspace root, in order.
+pub const IGNORE_FILES: &[&str] = &[".oqtoignore", ".octoignore"];
+
+/// Name of the manifest at the root of every archive.
+pub const MANIFEST_NAME: &str = ".oqto-manifest.json";
+
+/// Most excluded paths listed in a manifest or estimate.
+const MAX_LISTED_EXCLUSIONS: usize = 1000;
+
+/// One exclusion pattern.
+#[derive(Debug, Clone)]
+struct Rule {
+    /// Pattern as written, reported in manifests.
+    source: String,
+    segments: Vec<String>,
+    negated: bool,
+    dir_only: bool,
+}
+
+impl Rule {
+    /// Parse a pattern. Unanchored patterns match at any depth; `anchor_paths`
+    /// anchors patterns containing a `/` (gitignore), otherwise only a leading
+    /// `/` anchors.
+    fn parse(line: &str, anchor_paths: bool) -> Option<Self> {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            return None;
+        }
+        let (negated, pattern) = match line.strip_prefix('!') {
+            Some(rest) => (true, rest),
+            None => (false, line),
+        };
+        let (dir_only, pattern) = match pattern.strip_suffix('/') {
+            Some(rest) => (true, rest),
+            None => (false, pattern),
+        };
+        let anchored = pattern.starts_with('/') || (anchor_paths && pattern.contains('/'));
+        let mut segments: Vec<String> = pattern
+            .split('/')
+            .filter(|s| !s.is_empty())
+            .map(str::to_string)
+            .collect();
+        if segments.is_empty() {
+            return None;
+        }
+        if !anchored {
+            segments.insert(0, "**".to_string());
+        }
+        Some(Self {
+            source: line.to_string(),
+            segments,
+            negated,
+            dir_only,
+        })
+    }
+
+    fn matches(&self, path: &[&str], is_dir: bool) -> bool {
+        (is_dir || !self.dir_only) && match_segments(&self.segments, path)
+    }
+}
+
+fn match_segments(pattern: &[String], path: &[&str]) -> bool {
+    match pattern.split_first() {
+        None => path.is_empty(),
+        Some((first, rest)) if first == "**" => {
+            (0..=path.len()).any(|skip| match_segments(rest, &path[skip..]))
+        }
+        Some((first, rest)) => path
+            .split_first()
+            .is_some_and(|(name, tail)| match_name(first, name) && match_segments(rest, tail)),
+    }
+}
+
+/// Match one path segment against `*` / `?` wildcards.
+fn match_name(pattern: &str, name: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let name: Vec<char> = name.chars().collect();
+    let (mut p, mut n) = (0, 0);
+    let mut backtrack: Option<(usize, usize)> = None;
+    while n < name.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                backtrack = Some((p, n));
+                p += 1;
+            }
+            Some(&c) if c == '?' || c == name[n] => {
+                p += 1;
+                n += 1;
+            }
+            _ => match backtrack {
+                Some((star, matched)) => {
+                    p = star + 1;
+                    n = matched + 1;
+                    backtrack = Some((star, matched + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+/// Exclusion rules of one workspace.
+#[derive(Debug, Clone, Default)]
+pub struct ExclusionRules {
+    rules: Vec<Rule>,
+}
+
+impl ExclusionRules {
+    /// Admin globs followed by the workspace's ignore files, so the workspace
+    /// can re-include what an admin glob excludes.
+    pub fn load(workspace_root: &Path, admin_globs: &[String]) -> Self {
+        let mut rules: Vec<Rule> = admin_globs
+            .iter()
+            .filter_map(|glob| Rule::parse(glob, false))
+            .collect();
+        for name in IGNORE_FILES {
+            if let Ok(content) = std::fs::read_to_string(workspace_root.join(name)) {
+                rules.extend(content.lines().filter_map(|line| Rule::parse(line, true)));
+            }
+        }
+        Self { rules }
+    }
+
+    /// Patterns in effect, as written.
+    pub fn patterns(&self) -> Vec<String> {
+        self.rules.iter().map(|rule| rule.source.clone()).collect()
+    }
+
+    /// The pattern excluding `relative` (a `/`-separated path from the
+    /// workspace root), if any. A stale manifest at the root is always
+    /// excluded, as the archive gets a fresh one.
+    pub fn excluded_by(&self, relative: &str, is_dir: bool) -> Option<&str> {
+        if relative == MANIFEST_NAME {
+            return Some(MANIFEST_NAME);
+        }
+        let path: Vec<&str> = relative.split('/').filter(|s| !s.is_empty()).collect();
+        let rule = self
+            .rules
+            .iter()
+            .rev()
+            .find(|rule| rule.matches(&path, is_dir))?;
+        (!rule.negated).then_some(rule.source.as_str())
+    }
+
+    /// Walk `dir`, skipping excluded entries (and everything below excluded
+    /// directories). `dir` itself is always included; each skipped entry is
+    /// passed to `on_excluded` with the matching pattern.
+    pub fn walk<'a>(
+        &'a self,
+        root: &'a Path,
+        dir: &Path,
+        mut on_excluded: impl FnMut(&DirEntry, &str) + 'a,
+    ) -> impl Iterator<Item = DirEntry> + 'a {
+        WalkDir::new(dir)
+            .into_iter()
+            .filter_entry(move |entry| {
+                if entry.depth() == 0 {
+                    return true;
+                }
+                let relative = get_relative_path(root, entry.path());
+                match self.excluded_by(&relative, entry.file_type().is_dir()) {
+                    Some(rule) => {
+                        on_excluded(entry, rule);
+                        false
+                    }
+                    None => true,
+                }
+            })
+            .filter_map(|entry| entry.ok())
+    }
+}
+
+/// A path left out of an archive.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ExcludedEntry {
+    pub path: String,
+    pub is_dir: bool,
+    /// Pattern that excluded it.
+    pub rule: String,
+}
+
+/// Collects excluded paths, listing at most [`MAX_LISTED_EXCLUSIONS`].
+#[derive(Debug, Default)]
+pub struct Exclusions {
+    pub listed: Vec<ExcludedEntry>,
+    pub count: u64,
+}
+
+impl Exclusions {
+    pub fn record(&mut self, root: &Path, entry: &DirEntry, rule: &str) {
+        self.count += 1;
+        if self.listed.len() < MAX_LISTED_EXCLUSIONS {
+            self.listed.push(ExcludedEntry {
+                path: get_relative_path(root, entry.path()),
+                is_dir: entry.file_type().is_dir(),
+                rule: rule.to_string(),
+            });
+        }
+    }
+}
+
+/// Written to [`MANIFEST_NAME`] in every archive.
+#[derive(Debug, Serialize)]
+pub struct ArchiveManifest {
+    /// Unix timestamp (seconds) of archive creation.
+    pub created_at: u64,
+    /// Files in the archive (excluding the manifest).
+    pub files: u64,
+    /// Uncompressed bytes of those files.
+    pub bytes: u64,
+    /// Exclusion patterns in effect.
+    pub rules: Vec<String>,
+    /// Number of excluded entries (a skipped directory counts once).
+    pub excluded_count: u64,
+    /// Excluded entries, capped at 1000.
+    pub excluded: Vec<ExcludedEntry>,
+}
+
+/// Dry run of an archive: what it would contain and leave out.
+#[derive(Debug, Serialize)]
+pub struct ArchiveEstimate {
+    pub files: u64,
+    pub bytes: u64,
+    /// Files left out, including those below excluded directories.
+    pub excluded_files: u64,
+    pub excluded_bytes: u64,
+    pub rules: Vec<String>,
+    pub excluded_count: u64,
+    pub excluded: Vec<ExcludedEntry>,
+    /// Zip limits of the fileserver (0 = no limit).
+    pub max_bytes: u64,
+    pub max_entries: u64,
+    /// Whether the archive would be within those limits.
+    pub within_limits: bool,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_rules_follow_ignore_and_admin_globs() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        std::fs::write(
+            root.join(".oqtoignore"),
+            "# build output\n*.log\n/dist/\n!keep/target/\n",
+        )
+        .unwrap();
+        let rules = ExclusionRules::load(
+            root,
+            &["node_modules/**".to_string(), "target/".to_string()],
+        );
+
+        assert_eq!(rules.excluded_by("app.log", false), Some("*.log"));
+        assert_eq!(rules.excluded_by("src/debug.log", false), Some("*.log"));
+        assert_eq!(rules.excluded_by("dist", true), Some("/dist/"));
+        assert_eq!(rules.excluded_by("src/dist", true), None);
+        assert_eq!(
+            rules.excluded_by("web/node_modules/react/index.js", false),
+            Some("node_modules/**")
+        );
+        assert_eq!(rules.excluded_by("crates/a/target", true), Some("target/"));
+        assert_eq!(rules.excluded_by("target", false), None);
+        assert_eq!(rules.excluded_by("keep/target", true), None);
+        assert_eq!(rules.excluded_by("src/main.rs", false), None);
+    }
+
+    #[test]
+    fn test_match_name_wildcards() {
+        assert!(match_name("*.rs", "main.rs"));
+        assert!(match_name("a*b*c", "aXXbYc"));
+        assert!(match_name("file?.txt", "file1.txt"));
+        assert!(!match_name("*.rs", "main.rs.bak"));
+        assert!(!match_name("file?.txt", "file.txt"));
+    }
+}
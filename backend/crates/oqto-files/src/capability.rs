@@ -0,0 +1,390 @@
+//! Per-session capability tokens.
+//!
+//! The backend mints a short-lived token for every request it proxies to a
+//! session's fileserver: an HS256 JWT signed with a secret that only the
+//! backend and this fileserver know, naming the session, the directory the
+//! request may touch and whether it may write. The secret is rotated each
+//! time the session's processes are (re)started, so knowing the fileserver
+//! port alone grants nothing.
+//!
+//! Container sessions get the secret as a file ([`KeySource::File`]) that is
+//! re-read whenever it changes, so a resumed container picks up the rotated
+//! secret even when its processes are restored from a checkpoint.
+//!
+//! Without a secret the fileserver refuses every request, unless it was
+//! started with `--allow-unauthenticated`.
+
+use std::path::{Component, Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime};
+
+use axum::extract::{Request, State};
+use axum::http::{HeaderMap, Method, header};
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use jsonwebtoken::{Algorithm, DecodingKey, EncodingKey, Header, Validation};
+use serde::{Deserialize, Serialize};
+use tracing::{debug, warn};
+
+use crate::{AppState, FileServerError};
+
+/// Header carrying the token. `Authorization: Bearer` is accepted as well.
+pub const TOKEN_HEADER: &str = "x-oqto-files-token";
+
+/// Environment variable the fileserver reads its secret from.
+pub const TOKEN_SECRET_ENV: &str = "OQTO_FILES_TOKEN_SECRET";
+
+/// Environment variable naming a file the fileserver reads its secret from.
+pub const TOKEN_SECRET_FILE_ENV: &str = "OQTO_FILES_TOKEN_SECRET_FILE";
+
+/// Environment variable that lets the fileserver run without a secret.
+pub const ALLOW_UNAUTHENTICATED_ENV: &str = "OQTO_FILES_ALLOW_UNAUTHENTICATED";
+
+/// What a token allows.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CapabilityScope {
+    /// GET and HEAD requests only.
+    Read,
+    /// Any request.
+    ReadWrite,
+}
+
+/// Claims of a capability token.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CapabilityClaims {
+    /// Session the token was minted for.
+    pub sub: String,
+    /// Absolute directory requests are confined to.
+    pub root: String,
+    pub scope: CapabilityScope,
+    pub iat: i64,
+    pub exp: i64,
+}
+
+/// Signing key shared by the backend and one session's fileserver.
+#[derive(Clone)]
+pub struct CapabilityKey {
+    encoding: EncodingKey,
+    decoding: DecodingKey,
+}
+
+impl std::fmt::Debug for CapabilityKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("CapabilityKey").finish_non_exhaustive()
+    }
+}
+
+impl CapabilityKey {
+    pub fn new(secret: &str) -> Self {
+        Self {
+            encoding: EncodingKey::from_secret(secret.as_bytes()),
+            decoding: DecodingKey::from_secret(secret.as_bytes()),
+        }
+    }
+
+    /// Mint a token for `session_id` confined to `root`, valid for `ttl`.
+    pub fn mint(
+        &self,
+        session_id: &str,
+        root: &str,
+        scope: CapabilityScope,
+        ttl: Duration,
+    ) -> Result<String, jsonwebtoken::errors::Error> {
+        let now = now_secs();
+        let claims = CapabilityClaims {
+            sub: session_id.to_string(),
+            root: root.to_string(),
+            scope,
+            iat: now,
+            exp: now + ttl.as_secs() as i64,
+        };
+        jsonwebtoken::encode(&Header::new(Algorithm::HS256), &claims, &self.encoding)
+    }
+
+    /// Check the signature and expiry of a token.
+    pub fn verify(&self, token: &str) -> Result<CapabilityClaims, FileServerError> {
+        let mut validation = Validation::new(Algorithm::HS256);
+        validation.leeway = 0;
+        validation.set_required_spec_claims(&["exp", "sub"]);
+        jsonwebtoken::decode::<CapabilityClaims>(token, &self.decoding, &validation)
+            .map(|data| data.claims)
+            .map_err(|e| {
+                debug!("Rejected capability token: {}", e);
+                FileServerError::Unauthorized
+            })
+    }
+}
+
+/// Where the fileserver's key comes from.
+#[derive(Debug)]
+pub enum KeySource {
+    /// A secret given at startup.
+    Fixed(CapabilityKey),
+    /// A file holding the secret, re-read when its modification time changes.
+    File {
+        path: PathBuf,
+        cached: Mutex<Option<(SystemTime, CapabilityKey)>>,
+    },
+}
+
+impl KeySource {
+    pub fn file(path: PathBuf) -> Self {
+        Self::File {
+            path,
+            cached: Mutex::new(None),
+        }
+    }
+
+    /// The current key.
+    pub fn key(&self) -> Result<CapabilityKey, FileServerError> {
+        let (path, cached) = match self {
+            Self::Fixed(key) => return Ok(key.clone()),
+            Self::File { path, cached } => (path, cached),
+        };
+        let modified = std::fs::metadata(path)?.modified()?;
+        let mut cached = cached.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some((at, key)) = cached.as_ref()
+            && *at == modified
+        {
+            return Ok(key.clone());
+        }
+        let secret = std::fs::read_to_string(path)?;
+        let secret = secret.trim();
+        if secret.is_empty() {
+            warn!("Token secret file {} is empty", path.display());
+            return Err(FileServerError::Unauthorized);
+        }
+        let key = CapabilityKey::new(secret);
+        *cached = Some((modified, key.clone()));
+        Ok(key)
+    }
+}
+
+fn now_secs() -> i64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_secs() as i64)
+        .unwrap_or_default()
+}
+
+fn request_token(headers: &HeaderMap) -> Option<&str> {
+    if let Some(token) = headers.get(TOKEN_HEADER).and_then(|v| v.to_str().ok()) {
+        return Some(token.trim());
+    }
+    headers
+        .get(header::AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "))
+        .map(str::trim)
+}
+
+/// The `directory` query parameter of a request, decoded.
+fn requested_directory(query: Option<&str>) -> Option<String> {
+    query?
+        .split('&')
+        .filter_map(|pair| pair.split_once('='))
+        .find(|(key, _)| *key == "directory")
+        .map(|(_, value)| {
+            let value = value.replace('+', " ");
+            urlencoding::decode(&value)
+                .map(|v| v.into_owned())
+                .unwrap_or(value)
+        })
+}
+
+/// Resolve the directory a request operates in (before the handler's own
+/// checks) and canonicalize it where possible.
+fn effective_root(root_dir: &Path, directory: Option<&str>) -> Result<PathBuf, FileServerError> {
+    let directory = directory.map(str::trim).filter(|d| !d.is_empty());
+    let path = match directory {
+        None => root_dir.to_path_buf(),
+        Some(d) if Path::new(d).is_absolute() => PathBuf::from(d),
+        Some(d) => root_dir.join(d),
+    };
+    match path.canonicalize() {
+        Ok(path) => Ok(path),
+        // Non-existent directories are rejected by the handler; refuse
+        // lexical escapes here so they cannot pass the prefix check.
+        Err(_) if path.components().any(|c| c == Component::ParentDir) => {
+            Err(FileServerError::PathTraversal)
+        }
+        Err(_) => Ok(path),
+    }
+}
+
+/// Check a request against its token's claims.
+pub fn authorize(
+    claims: &CapabilityClaims,
+    method: &Method,
+    root_dir: &Path,
+    query: Option<&str>,
+) -> Result<(), FileServerError> {
+    if claims.scope == CapabilityScope::Read && !matches!(*method, Method::GET | Method::HEAD) {
+        return Err(FileServerError::Forbidden("token is read-only".to_string()));
+    }
+    let allowed = Path::new(&claims.root);
+    let allowed = allowed
+        .canonicalize()
+        .unwrap_or_else(|_| allowed.to_path_buf());
+    let requested = effective_root(root_dir, requested_directory(query).as_deref())?;
+    if !requested.starts_with(&allowed) {
+        return Err(FileServerError::Forbidden(
+            "directory is outside the token's root".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+/// Middleware validating the capability token of every request except
+/// `/health`.
+pub async fn require_capability(
+    State(state): State<AppState>,
+    request: Request,
+    next: Next,
+) -> Response {
+    if request.uri().path() == "/health" {
+        return next.run(request).await;
+    }
+    let Some(source) = state.capability.as_deref() else {
+        if state.allow_unauthenticated {
+            return next.run(request).await;
+        }
+        return FileServerError::Unauthorized.into_response();
+    };
+
+    let result = request_token(request.headers())
+        .ok_or(FileServerError::Unauthorized)
+        .and_then(|token| source.key()?.verify(token))
+        .and_then(|claims| {
+            authorize(
+                &claims,
+                request.method(),
+                &state.root_dir,
+                request.uri().query(),
+            )
+        });
+    match result {
+        Ok(()) => next.run(request).await,
+        Err(e) => e.into_response(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HOUR: Duration = Duration::from_secs(3600);
+
+    #[test]
+    fn test_token_roundtrip_and_wrong_secret() {
+        let key = CapabilityKey::new("secret-a");
+        let token = key
+            .mint("ses_1", "/home/alice", CapabilityScope::ReadWrite, HOUR)
+            .unwrap();
+        let claims = key.verify(&token).unwrap();
+        assert_eq!(claims.sub, "ses_1");
+        assert_eq!(claims.root, "/home/alice");
+        assert_eq!(claims.scope, CapabilityScope::ReadWrite);
+
+        assert!(CapabilityKey::new("secret-b").verify(&token).is_err());
+        assert!(key.verify("not-a-token").is_err());
+    }
+
+    #[test]
+    fn test_expired_token_is_rejected() {
+        let key = CapabilityKey::new("secret");
+        let claims = CapabilityClaims {
+            sub: "ses_1".to_string(),
+            root: "/".to_string(),
+            scope: CapabilityScope::Read,
+            iat: now_secs() - 120,
+            exp: now_secs() - 60,
+        };
+        let token =
+            jsonwebtoken::encode(&Header::new(Algorithm::HS256), &claims, &key.encoding).unwrap();
+        assert!(matches!(
+            key.verify(&token),
+            Err(FileServerError::Unauthorized)
+        ));
+    }
+
+    #[test]
+    fn test_key_file_is_reread_when_it_changes() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("token-secret");
+        std::fs::write(&path, "secret-a\n").unwrap();
+        let source = KeySource::file(path.clone());
+        let token = CapabilityKey::new("secret-a")
+            .mint("ses_1", "/", CapabilityScope::Read, HOUR)
+            .unwrap();
+        assert!(source.key().unwrap().verify(&token).is_ok());
+
+        // Rewritten the way the backend does on resume: a new file renamed
+        // over the old one.
+        let next = dir.path().join("token-secret.new");
+        std::fs::write(&next, "secret-b").unwrap();
+        let old = std::fs::metadata(&path).unwrap().modified().unwrap();
+        let file = std::fs::File::options().write(true).open(&next).unwrap();
+        file.set_modified(old + Duration::from_secs(1)).unwrap();
+        std::fs::rename(&next, &path).unwrap();
+        assert!(source.key().unwrap().verify(&token).is_err());
+
+        std::fs::remove_file(&path).unwrap();
+        assert!(source.key().is_err());
+    }
+
+    #[test]
+    fn test_authorize_scope_and_root() {
+        let workspace = tempfile::tempdir().unwrap();
+        let project = workspace.path().join("project");
+        std::fs::create_dir(&project).unwrap();
+        let claims = |root: &Path, scope| CapabilityClaims {
+            sub: "ses_1".to_string(),
+            root: root.display().to_string(),
+            scope,
+            iat: 0,
+            exp: 0,
+        };
+
+        let read_only = claims(workspace.path(), CapabilityScope::Read);
+        assert!(authorize(&read_only, &Method::GET, workspace.path(), None).is_ok());
+        assert!(authorize(&read_only, &Method::PUT, workspace.path(), None).is_err());
+
+        let project_only = claims(&project, CapabilityScope::ReadWrite);
+        let query = format!(
+            "path=a.txt&directory={}",
+            urlencoding::encode(&project.display().to_string())
+        );
+        assert!(authorize(&project_only, &Method::POST, workspace.path(), Some(&query)).is_ok());
+        assert!(
+            authorize(
+                &project_only,
+                &Method::GET,
+                workspace.path(),
+                Some("directory=project")
+            )
+            .is_ok()
+        );
+        // The workspace root and escapes are outside the token's root.
+        assert!(authorize(&project_only, &Method::GET, workspace.path(), None).is_err());
+        assert!(
+            authorize(
+                &project_only,
+                &Method::GET,
+                workspace.path(),
+                Some("directory=project%2F..%2F..")
+            )
+            .is_err()
+        );
+        assert!(
+            authorize(
+                &project_only,
+                &Method::GET,
+                workspace.path(),
+                Some("directory=project%2Fmissing%2F..%2F..")
+            )
+            .is_err()
+        );
+    }
+}
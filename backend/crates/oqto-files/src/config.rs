@@ -9,6 +9,16 @@ pub struct Config {
     #[serde(default = "default_max_upload_size")]
     pub max_upload_size: u64,
 
+    /// Maximum total size of a resumable upload (in bytes), whose chunks
+    /// are each bounded by `max_upload_size`. Set to 0 for no limit.
+    #[serde(default)]
+    pub max_resumable_upload_size: u64,
+
+    /// Seconds after which a resumable upload that stopped receiving data
+    /// is discarded (defaults to 24 hours)
+    #[serde(default = "default_upload_expiry_secs")]
+    pub upload_expiry_secs: u64,
+
     /// Maximum total uncompressed size for zip downloads (in bytes). Set to 0 for no limit.
     #[serde(default = "default_max_zip_bytes")]
     pub max_zip_bytes: u64,
@@ -17,6 +27,11 @@ pub struct Config {
     #[serde(default = "default_max_zip_entries")]
     pub max_zip_entries: u64,
 
+    /// Globs left out of zip archives, on top of the workspace's
+    /// `.oqtoignore` (e.g. `node_modules/**`, `target/**`).
+    #[serde(default)]
+    pub archive_exclude: Vec<String>,
+
     /// Maximum depth for directory traversal
     #[serde(default = "default_max_depth")]
     pub max_depth: usize,
@@ -46,6 +61,10 @@ fn default_max_upload_size() -> u64 {
     100 * 1024 * 1024 // 100 MB
 }
 
+fn default_upload_expiry_secs() -> u64 {
+    24 * 60 * 60 // 24 hours
+}
+
 fn default_max_zip_bytes() -> u64 {
     500 * 1024 * 1024 // 500 MB
 }
@@ -77,6 +96,7 @@ fn default_hidden_dirs() -> Vec<String> {
         "target".to_string(),
         ".venv".to_string(),
         "venv".to_string(),
+        crate::uploads::STAGING_DIR.to_string(),
     ]
 }
 
@@ -122,8 +142,11 @@ impl Default for Config {
     fn default() -> Self {
         Self {
             max_upload_size: default_max_upload_size(),
+            max_resumable_upload_size: 0,
+            upload_expiry_secs: default_upload_expiry_secs(),
             max_zip_bytes: default_max_zip_bytes(),
             max_zip_entries: default_max_zip_entries(),
+            archive_exclude: Vec::new(),
             max_depth: default_max_depth(),
             hidden_extensions: default_hidden_extensions(),
             hidden_dirs: default_hidden_dirs(),
@@ -37,6 +37,21 @@ pub enum FileServerError {
 
     #[error("Failed to create directory: {0}")]
     CreateDirFailed(String),
+
+    #[error("Missing or invalid capability token")]
+    Unauthorized,
+
+    #[error("Not allowed by capability token: {0}")]
+    Forbidden(String),
+
+    #[error("Upload is at offset {expected}, not {got}")]
+    UploadOffsetMismatch { expected: u64, got: u64 },
+
+    #[error("Upload is incomplete: {offset} of {size} bytes received")]
+    UploadIncomplete { offset: u64, size: u64 },
+
+    #[error("Checksum mismatch: expected {expected}, got {actual}")]
+    ChecksumMismatch { expected: String, actual: String },
 }
 
 #[derive(Serialize)]
@@ -64,6 +79,15 @@ impl IntoResponse for FileServerError {
             FileServerError::CreateDirFailed(_) => {
                 (StatusCode::INTERNAL_SERVER_ERROR, "CREATE_DIR_FAILED")
             }
+            FileServerError::Unauthorized => (StatusCode::UNAUTHORIZED, "UNAUTHORIZED"),
+            FileServerError::Forbidden(_) => (StatusCode::FORBIDDEN, "FORBIDDEN"),
+            FileServerError::UploadOffsetMismatch { .. } => {
+                (StatusCode::CONFLICT, "UPLOAD_OFFSET_MISMATCH")
+            }
+            FileServerError::UploadIncomplete { .. } => (StatusCode::CONFLICT, "UPLOAD_INCOMPLETE"),
+            FileServerError::ChecksumMismatch { .. } => {
+                (StatusCode::UNPROCESSABLE_ENTITY, "CHECKSUM_MISMATCH")
+            }
         };
 
         let body = ErrorResponse {
@@ -8,15 +8,15 @@ use axum::extract::ws::{Message, WebSocket, WebSocketUpgrade};
 use axum::{
     Json,
     body::Body,
-    extract::{Multipart, Query, State},
-    http::{StatusCode, header},
+    extract::{Multipart, Path as AxumPath, Query, State},
+    http::{HeaderMap, StatusCode, header},
     response::{IntoResponse, Response},
 };
 use image::GenericImageView;
 use image::codecs::jpeg::JpegEncoder;
 use notify::{
     EventKind, RecursiveMode, Watcher,
-    event::{CreateKind, RemoveKind},
+    event::{CreateKind, ModifyKind, RemoveKind},
 };
 use serde::{Deserialize, Serialize};
 use sha2::{Digest, Sha256};
@@ -38,7 +38,9 @@ use zip::write::SimpleFileOptions;
 
 use crate::AppState;
 use crate::Config;
+use crate::archive::{ArchiveEstimate, ArchiveManifest, ExclusionRules, Exclusions, MANIFEST_NAME};
 use crate::error::FileServerError;
+use crate::uploads;
 
 // Lazy-loaded syntax highlighting assets
 static SYNTAX_SET: LazyLock<SyntaxSet> = LazyLock::new(SyntaxSet::load_defaults_newlines);
@@ -139,6 +141,56 @@ pub struct UploadQuery {
     pub mkdir: bool,
 }
 
+/// Request body for starting a resumable upload
+#[derive(Debug, Deserialize)]
+pub struct CreateUploadRequest {
+    /// Total size in bytes
+    pub size: u64,
+    /// Expected hex SHA-256 of the whole file, checked on completion
+    #[serde(default)]
+    pub sha256: Option<String>,
+}
+
+/// Query parameters naming a resumable upload's root
+#[derive(Debug, Deserialize)]
+pub struct UploadIdQuery {
+    /// Directory the upload was started with
+    pub directory: Option<String>,
+}
+
+/// Query parameters for appending a chunk
+#[derive(Debug, Deserialize)]
+pub struct UploadChunkQuery {
+    /// Directory the upload was started with
+    pub directory: Option<String>,
+    /// Offset the chunk starts at; must match the bytes received so far
+    pub offset: u64,
+}
+
+/// State of a resumable upload
+#[derive(Debug, Serialize)]
+pub struct UploadStatusResponse {
+    pub id: String,
+    pub path: String,
+    pub size: u64,
+    /// Bytes received so far; the next chunk starts here
+    pub offset: u64,
+    /// Suggested chunk size in bytes
+    pub chunk_size: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sha256: Option<String>,
+}
+
+/// Response for a completed resumable upload
+#[derive(Debug, Serialize)]
+pub struct CompletedUploadResponse {
+    pub success: bool,
+    pub path: String,
+    pub size: u64,
+    /// SHA-256 of the stored file
+    pub sha256: String,
+}
+
 /// Response for successful operations
 #[derive(Debug, Serialize)]
 pub struct SuccessResponse {
@@ -406,7 +458,7 @@ fn resolve_request_root(root: &Path, directory: Option<&str>) -> Result<PathBuf,
 /// Get relative path from root.
 ///
 /// Always uses `/` as separator (zip + HTTP-friendly).
-fn get_relative_path(root: &Path, full_path: &Path) -> String {
+pub(crate) fn get_relative_path(root: &Path, full_path: &Path) -> String {
     let Ok(relative) = full_path.strip_prefix(root) else {
         return String::new();
     };
@@ -448,8 +500,17 @@ fn parse_extension_filter(ext: &Option<String>) -> Option<HashSet<String>> {
     if set.is_empty() { None } else { Some(set) }
 }
 
-fn event_label(kind: &EventKind, is_dir: bool) -> Option<&'static str> {
+/// Label of a watch event. `exists` is whether the path still exists when
+/// the batch is flushed: a rename reports the old path as deleted and the new
+/// one as created.
+fn event_label(kind: &EventKind, is_dir: bool, exists: bool) -> Option<&'static str> {
     match kind {
+        EventKind::Modify(ModifyKind::Name(_)) => match (exists, is_dir) {
+            (true, true) => Some("dir_created"),
+            (true, false) => Some("file_created"),
+            (false, true) => Some("dir_deleted"),
+            (false, false) => Some("file_deleted"),
+        },
         EventKind::Create(_) => {
             if is_dir {
                 Some("dir_created")
@@ -575,7 +636,9 @@ async fn watch_socket(
                         continue;
                     }
 
-                    let is_dir = match fs::metadata(&path).await {
+                    let metadata = fs::metadata(&path).await;
+                    let exists = metadata.is_ok();
+                    let is_dir = match metadata {
                         Ok(metadata) => metadata.is_dir(),
                         Err(_) => matches!(
                             kind,
@@ -595,12 +658,15 @@ async fn watch_socket(
                         }
                     }
 
-                    let Some(event_type) = event_label(&kind, is_dir) else {
+                    let Some(event_type) = event_label(&kind, is_dir, exists) else {
                         continue;
                     };
 
                     let relative_path = get_relative_path(&root_dir, &path);
-                    if relative_path.is_empty() {
+                    // Chunks of resumable uploads are not workspace changes.
+                    if relative_path.is_empty()
+                        || Path::new(&relative_path).starts_with(uploads::STAGING_DIR)
+                    {
                         continue;
                     }
 
@@ -1079,32 +1145,7 @@ pub async fn upload_file(
     };
 
     // Re-validate the final path is within root (belt-and-suspenders)
-    let canonical_root = root_dir.canonicalize().map_err(FileServerError::Io)?;
-    if final_path.exists() {
-        let metadata = fs::symlink_metadata(&final_path)
-            .await
-            .map_err(FileServerError::Io)?;
-        if metadata.file_type().is_symlink() {
-            warn!("Refusing to overwrite symlink: {:?}", final_path);
-            return Err(FileServerError::PathTraversal);
-        }
-        if metadata.is_dir() {
-            return Err(FileServerError::NotAFile);
-        }
-        let canonical_path = final_path.canonicalize().map_err(FileServerError::Io)?;
-        if !canonical_path.starts_with(&canonical_root) {
-            warn!("Final path resolved outside root: {:?}", final_path);
-            return Err(FileServerError::PathTraversal);
-        }
-    } else if let Some(parent) = final_path.parent()
-        && parent.exists()
-    {
-        let canonical_parent = parent.canonicalize().map_err(FileServerError::Io)?;
-        if !canonical_parent.starts_with(&canonical_root) {
-            warn!("Final path parent outside root: {:?}", final_path);
-            return Err(FileServerError::PathTraversal);
-        }
-    }
+    check_upload_target(&root_dir, &final_path).await?;
 
     let parent_dir = final_path
         .parent()
@@ -1163,6 +1204,227 @@ pub async fn upload_file(
     }))
 }
 
+/// Check that an upload may be written to `final_path`: inside root, and
+/// not replacing a symlink or a directory.
+async fn check_upload_target(root_dir: &Path, final_path: &Path) -> Result<(), FileServerError> {
+    let canonical_root = root_dir.canonicalize().map_err(FileServerError::Io)?;
+    if final_path.exists() {
+        let metadata = fs::symlink_metadata(final_path)
+            .await
+            .map_err(FileServerError::Io)?;
+        if metadata.file_type().is_symlink() {
+            warn!("Refusing to overwrite symlink: {:?}", final_path);
+            return Err(FileServerError::PathTraversal);
+        }
+        if metadata.is_dir() {
+            return Err(FileServerError::NotAFile);
+        }
+        let canonical_path = final_path.canonicalize().map_err(FileServerError::Io)?;
+        if !canonical_path.starts_with(&canonical_root) {
+            warn!("Final path resolved outside root: {:?}", final_path);
+            return Err(FileServerError::PathTraversal);
+        }
+    } else if let Some(parent) = final_path.parent()
+        && parent.exists()
+    {
+        let canonical_parent = parent.canonicalize().map_err(FileServerError::Io)?;
+        if !canonical_parent.starts_with(&canonical_root) {
+            warn!("Final path parent outside root: {:?}", final_path);
+            return Err(FileServerError::PathTraversal);
+        }
+    }
+    Ok(())
+}
+
+/// Resolve the destination of a resumable upload, creating its parent
+/// directory if asked to.
+async fn resumable_upload_target(
+    root_dir: &Path,
+    path: &str,
+    mkdir: bool,
+) -> Result<PathBuf, FileServerError> {
+    let dest_path = resolve_path(root_dir, path)?;
+    if path.ends_with('/') || dest_path == root_dir {
+        return Err(FileServerError::InvalidPath(
+            "Resumable uploads need a file path".to_string(),
+        ));
+    }
+    if Path::new(path.trim_start_matches('/')).starts_with(uploads::STAGING_DIR) {
+        return Err(FileServerError::InvalidPath(path.to_string()));
+    }
+    let parent = dest_path
+        .parent()
+        .ok_or_else(|| FileServerError::InvalidPath("Missing parent directory".to_string()))?;
+    if !parent.exists() {
+        if !mkdir {
+            let missing = get_relative_path(root_dir, parent);
+            return Err(FileServerError::NotFound(missing));
+        }
+        fs::create_dir_all(parent).await.map_err(|e| {
+            error!("Failed to create directory: {}", e);
+            FileServerError::CreateDirFailed(parent.display().to_string())
+        })?;
+    }
+    check_upload_target(root_dir, &dest_path).await?;
+    Ok(dest_path)
+}
+
+fn upload_status(
+    state: &AppState,
+    meta: uploads::UploadMeta,
+    offset: u64,
+) -> Json<UploadStatusResponse> {
+    Json(UploadStatusResponse {
+        id: meta.id,
+        path: meta.path,
+        size: meta.size,
+        offset,
+        chunk_size: uploads::DEFAULT_CHUNK_SIZE.min(state.config.max_upload_size),
+        sha256: meta.sha256,
+    })
+}
+
+/// POST /uploads - Start a resumable upload
+pub async fn create_upload(
+    State(state): State<AppState>,
+    Query(query): Query<UploadQuery>,
+    Json(request): Json<CreateUploadRequest>,
+) -> Result<(StatusCode, Json<UploadStatusResponse>), FileServerError> {
+    let limit = state.config.max_resumable_upload_size;
+    if limit > 0 && request.size > limit {
+        return Err(FileServerError::FileTooLarge {
+            size: request.size,
+            limit,
+        });
+    }
+    let root_dir = resolve_request_root(&state.root_dir, query.directory.as_deref())?;
+    let dest_path = resumable_upload_target(&root_dir, &query.path, query.mkdir).await?;
+
+    let meta = uploads::UploadMeta {
+        id: String::new(),
+        path: get_relative_path(&root_dir, &dest_path),
+        size: request.size,
+        sha256: request.sha256,
+        mkdir: query.mkdir,
+        created_at: uploads::now_secs(),
+    };
+    let store = uploads::UploadStore::new(&root_dir);
+    let expiry = Duration::from_secs(state.config.upload_expiry_secs);
+    let meta = tokio::task::spawn_blocking(move || {
+        store.prune(expiry);
+        store.create(meta)
+    })
+    .await
+    .map_err(|err| FileServerError::Io(std::io::Error::other(err.to_string())))??;
+
+    info!(
+        "Started resumable upload {} for {} ({} bytes)",
+        meta.id, meta.path, meta.size
+    );
+    Ok((StatusCode::CREATED, upload_status(&state, meta, 0)))
+}
+
+/// GET /uploads/{id} - Offset to resume a resumable upload from
+pub async fn get_upload(
+    State(state): State<AppState>,
+    AxumPath(id): AxumPath<String>,
+    Query(query): Query<UploadIdQuery>,
+) -> Result<Json<UploadStatusResponse>, FileServerError> {
+    let root_dir = resolve_request_root(&state.root_dir, query.directory.as_deref())?;
+    let store = uploads::UploadStore::new(&root_dir);
+    let (meta, offset) = tokio::task::spawn_blocking(move || store.load(&id))
+        .await
+        .map_err(|err| FileServerError::Io(std::io::Error::other(err.to_string())))??;
+    Ok(upload_status(&state, meta, offset))
+}
+
+/// PATCH /uploads/{id} - Append a chunk to a resumable upload
+pub async fn upload_chunk(
+    State(state): State<AppState>,
+    AxumPath(id): AxumPath<String>,
+    Query(query): Query<UploadChunkQuery>,
+    headers: HeaderMap,
+    body: axum::body::Bytes,
+) -> Result<Json<UploadStatusResponse>, FileServerError> {
+    let root_dir = resolve_request_root(&state.root_dir, query.directory.as_deref())?;
+    let digest = headers
+        .get(uploads::CHUNK_DIGEST_HEADER)
+        .and_then(|v| v.to_str().ok())
+        .map(str::to_string);
+    let store = uploads::UploadStore::new(&root_dir);
+    let (meta, offset) = tokio::task::spawn_blocking(move || {
+        let offset = store.append(&id, query.offset, &body, digest.as_deref())?;
+        let (meta, _) = store.load(&id)?;
+        Ok::<_, FileServerError>((meta, offset))
+    })
+    .await
+    .map_err(|err| FileServerError::Io(std::io::Error::other(err.to_string())))??;
+    debug!("Upload {} at {} of {} bytes", meta.id, offset, meta.size);
+    Ok(upload_status(&state, meta, offset))
+}
+
+/// POST /uploads/{id}/complete - Verify a resumable upload and move it into place
+pub async fn complete_upload(
+    State(state): State<AppState>,
+    AxumPath(id): AxumPath<String>,
+    Query(query): Query<UploadIdQuery>,
+) -> Result<Json<CompletedUploadResponse>, FileServerError> {
+    let root_dir = resolve_request_root(&state.root_dir, query.directory.as_deref())?;
+    let store = uploads::UploadStore::new(&root_dir);
+    let (meta, data_path, sha256) = {
+        let store = store.clone();
+        tokio::task::spawn_blocking(move || store.finish(&id))
+            .await
+            .map_err(|err| FileServerError::Io(std::io::Error::other(err.to_string())))??
+    };
+
+    // The destination may have changed since the upload started.
+    let dest_path = resumable_upload_target(&root_dir, &meta.path, meta.mkdir).await?;
+    if dest_path.exists() {
+        fs::remove_file(&dest_path)
+            .await
+            .map_err(FileServerError::Io)?;
+    }
+    fs::rename(&data_path, &dest_path)
+        .await
+        .map_err(FileServerError::Io)?;
+    if let Err(e) = store.remove(&meta.id) {
+        warn!("Failed to remove staging of upload {}: {}", meta.id, e);
+    }
+
+    info!(
+        "Completed resumable upload: {} ({} bytes, sha256 {})",
+        dest_path.display(),
+        meta.size,
+        sha256
+    );
+    Ok(Json(CompletedUploadResponse {
+        success: true,
+        path: get_relative_path(&root_dir, &dest_path),
+        size: meta.size,
+        sha256,
+    }))
+}
+
+/// DELETE /uploads/{id} - Abort a resumable upload
+pub async fn cancel_upload(
+    State(state): State<AppState>,
+    AxumPath(id): AxumPath<String>,
+    Query(query): Query<UploadIdQuery>,
+) -> Result<Json<SuccessResponse>, FileServerError> {
+    let root_dir = resolve_request_root(&state.root_dir, query.directory.as_deref())?;
+    let store = uploads::UploadStore::new(&root_dir);
+    let message = format!("Upload cancelled: {}", id);
+    tokio::task::spawn_blocking(move || store.remove(&id))
+        .await
+        .map_err(|err| FileServerError::Io(std::io::Error::other(err.to_string())))??;
+    Ok(Json(SuccessResponse {
+        success: true,
+        message,
+        path: None,
+    }))
+}
+
 /// DELETE /file - Delete file or directory
 pub async fn delete_file(
     State(state): State<AppState>,
@@ -1439,8 +1701,10 @@ pub async fn download(
         let safe_zip_name = zip_name.replace('"', "'");
 
         let limits = ZipLimits::from_config(&state.config);
+        let excludes = state.config.archive_exclude.clone();
         let (zip_file, zip_size) =
-            create_zip_file_from_paths(root_dir.clone(), vec![path.clone()], limits).await?;
+            create_zip_file_from_paths(root_dir.clone(), vec![path.clone()], limits, excludes)
+                .await?;
         let body = Body::from_stream(ReaderStream::new(zip_file));
 
         Ok((
@@ -1459,14 +1723,9 @@ pub async fn download(
     }
 }
 
-/// GET /download-zip - Download multiple files/directories as a single zip
-pub async fn download_zip(
-    State(state): State<AppState>,
-    Query(query): Query<DownloadZipQuery>,
-) -> Result<Response, FileServerError> {
-    let root_dir = resolve_request_root(&state.root_dir, query.directory.as_deref())?;
-    // Parse comma-separated paths
-    let paths: Vec<&str> = query.paths.split(',').map(|s| s.trim()).collect();
+/// Resolve the comma-separated `paths` of a zip request.
+fn resolve_zip_paths(root_dir: &Path, paths: &str) -> Result<Vec<PathBuf>, FileServerError> {
+    let paths: Vec<&str> = paths.split(',').map(|s| s.trim()).collect();
 
     if paths.is_empty() {
         return Err(FileServerError::InvalidPath(
@@ -1477,17 +1736,29 @@ pub async fn download_zip(
     // Resolve and verify all paths
     let mut resolved_paths = Vec::new();
     for path_str in &paths {
-        let resolved = resolve_and_verify_path(&root_dir, path_str)?;
+        let resolved = resolve_and_verify_path(root_dir, path_str)?;
         if !resolved.exists() {
             return Err(FileServerError::NotFound(path_str.to_string()));
         }
         resolved_paths.push(resolved);
     }
+    Ok(resolved_paths)
+}
+
+/// GET /download-zip - Download multiple files/directories as a single zip
+pub async fn download_zip(
+    State(state): State<AppState>,
+    Query(query): Query<DownloadZipQuery>,
+) -> Result<Response, FileServerError> {
+    let root_dir = resolve_request_root(&state.root_dir, query.directory.as_deref())?;
+    let resolved_paths = resolve_zip_paths(&root_dir, &query.paths)?;
 
     debug!("Downloading {} items as zip", resolved_paths.len());
 
     let limits = ZipLimits::from_config(&state.config);
-    let (zip_file, zip_size) = create_zip_file_from_paths(root_dir, resolved_paths, limits).await?;
+    let excludes = state.config.archive_exclude.clone();
+    let (zip_file, zip_size) =
+        create_zip_file_from_paths(root_dir, resolved_paths, limits, excludes).await?;
     let body = Body::from_stream(ReaderStream::new(zip_file));
 
     let zip_name = query.name.unwrap_or_else(|| "download.zip".to_string());
@@ -1508,19 +1779,102 @@ pub async fn download_zip(
         .into_response())
 }
 
+/// GET /download-zip/estimate - Dry run of a zip download
+///
+/// Reports the size of the archive `paths` would produce, what the exclusion
+/// rules leave out, and whether it fits the zip limits.
+pub async fn estimate_zip(
+    State(state): State<AppState>,
+    Query(query): Query<DownloadZipQuery>,
+) -> Result<Json<ArchiveEstimate>, FileServerError> {
+    let root_dir = resolve_request_root(&state.root_dir, query.directory.as_deref())?;
+    let resolved_paths = resolve_zip_paths(&root_dir, &query.paths)?;
+    let limits = ZipLimits::from_config(&state.config);
+    let excludes = state.config.archive_exclude.clone();
+
+    let estimate = tokio::task::spawn_blocking(move || {
+        let rules = ExclusionRules::load(&root_dir, &excludes);
+        estimate_zip_blocking(&root_dir, &resolved_paths, limits, &rules)
+    })
+    .await
+    .map_err(|err| FileServerError::Io(std::io::Error::other(err.to_string())))??;
+    Ok(Json(estimate))
+}
+
+fn estimate_zip_blocking(
+    root: &Path,
+    paths: &[PathBuf],
+    limits: ZipLimits,
+    rules: &ExclusionRules,
+) -> Result<ArchiveEstimate, FileServerError> {
+    let mut exclusions = Exclusions::default();
+    let mut excluded_dirs = Vec::new();
+    let (mut files, mut bytes) = (0u64, 0u64);
+    let (mut excluded_files, mut excluded_bytes) = (0u64, 0u64);
+
+    for path in paths {
+        if path.is_file() {
+            files += 1;
+            bytes += std::fs::metadata(path)?.len();
+            continue;
+        }
+        let walk = rules.walk(root, path, |entry, rule| {
+            exclusions.record(root, entry, rule);
+            if entry.file_type().is_dir() {
+                excluded_dirs.push(entry.path().to_path_buf());
+            } else {
+                excluded_files += 1;
+                excluded_bytes += entry.metadata().map(|m| m.len()).unwrap_or(0);
+            }
+        });
+        for entry in walk {
+            if entry.file_type().is_file() {
+                files += 1;
+                bytes += entry.metadata().map(|m| m.len()).unwrap_or(0);
+            }
+        }
+    }
+    for dir in excluded_dirs {
+        for entry in WalkDir::new(dir).into_iter().filter_map(|e| e.ok()) {
+            if entry.file_type().is_file() {
+                excluded_files += 1;
+                excluded_bytes += entry.metadata().map(|m| m.len()).unwrap_or(0);
+            }
+        }
+    }
+
+    Ok(ArchiveEstimate {
+        files,
+        bytes,
+        excluded_files,
+        excluded_bytes,
+        rules: rules.patterns(),
+        excluded_count: exclusions.count,
+        excluded: exclusions.listed,
+        max_bytes: limits.max_bytes,
+        max_entries: limits.max_entries,
+        within_limits: (limits.max_entries == 0 || files <= limits.max_entries)
+            && (limits.max_bytes == 0 || bytes <= limits.max_bytes),
+    })
+}
+
 /// Create a zip archive from a list of paths (files or directories).
 ///
 /// Uses a temporary file on disk so large downloads don't require buffering
-/// the full archive in memory.
+/// the full archive in memory. Entries matching the workspace's exclusion
+/// rules are left out and recorded in the archive's manifest.
 async fn create_zip_file_from_paths(
     root: PathBuf,
     paths: Vec<PathBuf>,
     limits: ZipLimits,
+    excludes: Vec<String>,
 ) -> Result<(fs::File, u64), FileServerError> {
-    let (file, size) =
-        tokio::task::spawn_blocking(move || create_zip_tempfile_blocking(&root, &paths, limits))
-            .await
-            .map_err(|err| FileServerError::Io(std::io::Error::other(err.to_string())))??;
+    let (file, size) = tokio::task::spawn_blocking(move || {
+        let rules = ExclusionRules::load(&root, &excludes);
+        create_zip_tempfile_blocking(&root, &paths, limits, &rules)
+    })
+    .await
+    .map_err(|err| FileServerError::Io(std::io::Error::other(err.to_string())))??;
 
     Ok((fs::File::from_std(file), size))
 }
@@ -1529,13 +1883,26 @@ fn create_zip_tempfile_blocking(
     root: &Path,
     paths: &[PathBuf],
     limits: ZipLimits,
+    rules: &ExclusionRules,
 ) -> Result<(std::fs::File, u64), FileServerError> {
-    enforce_zip_limits(root, paths, limits)?;
+    enforce_zip_limits(root, paths, limits, rules)?;
     let file = tempfile()?;
     let mut zip = ZipWriter::new(file);
     let options = SimpleFileOptions::default()
         .compression_method(zip::CompressionMethod::Deflated)
         .unix_permissions(0o644);
+    let mut manifest = ArchiveManifest {
+        created_at: SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or_default(),
+        files: 0,
+        bytes: 0,
+        rules: rules.patterns(),
+        excluded_count: 0,
+        excluded: Vec::new(),
+    };
+    let mut exclusions = Exclusions::default();
 
     for path in paths {
         if path.is_file() {
@@ -1547,16 +1914,36 @@ fn create_zip_tempfile_blocking(
             } else {
                 relative
             };
+            if file_name == MANIFEST_NAME {
+                continue;
+            }
 
             zip.start_file(&file_name, options)
                 .map_err(zip_error_to_fileserver_error)?;
             let mut input = std::fs::File::open(path)?;
-            std::io::copy(&mut input, &mut zip)?;
+            manifest.bytes += std::io::copy(&mut input, &mut zip)?;
+            manifest.files += 1;
         } else if path.is_dir() {
-            add_directory_to_zip(&mut zip, root, path, options)?;
+            add_directory_to_zip(
+                &mut zip,
+                root,
+                path,
+                options,
+                rules,
+                &mut manifest,
+                &mut exclusions,
+            )?;
         }
     }
 
+    manifest.excluded_count = exclusions.count;
+    manifest.excluded = exclusions.listed;
+    let manifest = serde_json::to_vec_pretty(&manifest)
+        .map_err(|err| FileServerError::Io(std::io::Error::other(err.to_string())))?;
+    zip.start_file(MANIFEST_NAME, options)
+        .map_err(zip_error_to_fileserver_error)?;
+    zip.write_all(&manifest)?;
+
     let mut file = zip.finish().map_err(zip_error_to_fileserver_error)?;
     file.flush()?;
 
@@ -1570,14 +1957,20 @@ fn zip_error_to_fileserver_error(error: zip::result::ZipError) -> FileServerErro
     FileServerError::Io(std::io::Error::other(error.to_string()))
 }
 
-/// Recursively add a directory to a zip archive.
+/// Recursively add a directory to a zip archive, skipping excluded entries.
 fn add_directory_to_zip<W: Write + Seek>(
     zip: &mut ZipWriter<W>,
     root: &Path,
     dir: &Path,
     options: SimpleFileOptions,
+    rules: &ExclusionRules,
+    manifest: &mut ArchiveManifest,
+    exclusions: &mut Exclusions,
 ) -> Result<(), FileServerError> {
-    for entry in WalkDir::new(dir).into_iter().filter_map(|e| e.ok()) {
+    let walk = rules.walk(root, dir, |entry, rule| {
+        exclusions.record(root, entry, rule)
+    });
+    for entry in walk {
         let entry_path = entry.path();
         let relative = get_relative_path(root, entry_path);
 
@@ -1585,7 +1978,8 @@ fn add_directory_to_zip<W: Write + Seek>(
             zip.start_file(&relative, options)
                 .map_err(zip_error_to_fileserver_error)?;
             let mut input = std::fs::File::open(entry_path)?;
-            std::io::copy(&mut input, zip)?;
+            manifest.bytes += std::io::copy(&mut input, zip)?;
+            manifest.files += 1;
         } else if entry.file_type().is_dir() && entry_path != dir {
             let dir_name = format!("{}/", relative);
             zip.add_directory(&dir_name, options)
@@ -1600,6 +1994,7 @@ fn enforce_zip_limits(
     root: &Path,
     paths: &[PathBuf],
     limits: ZipLimits,
+    rules: &ExclusionRules,
 ) -> Result<(), FileServerError> {
     let mut total_bytes = 0u64;
     let mut total_entries = 0u64;
@@ -1611,10 +2006,7 @@ fn enforce_zip_limits(
         }
 
         if path.is_dir() {
-            for entry in WalkDir::new(path)
-                .into_iter()
-                .filter_map(|entry| entry.ok())
-            {
+            for entry in rules.walk(root, path, |_, _| {}) {
                 if entry.file_type().is_file() {
                     track_zip_entry(entry.path(), &mut total_bytes, &mut total_entries, limits)?;
                 }
@@ -1981,7 +2373,7 @@ fn generate_video_thumbnail_blocking(
 #[cfg(test)]
 mod tests {
     use super::*;
-    use notify::event::ModifyKind;
+    use notify::event::RenameMode;
     use std::fs;
     use std::io::Read;
     use std::path::PathBuf;
@@ -2129,6 +2521,7 @@ mod tests {
             root,
             &[root.join("nested"), root.join("root.txt")],
             limits,
+            &ExclusionRules::default(),
         )
         .unwrap();
         assert!(size > 0);
@@ -2150,6 +2543,59 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_zip_skips_excluded_entries_and_writes_manifest() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+
+        fs::write(root.join(".oqtoignore"), "*.log\n").unwrap();
+        fs::write(root.join("main.rs"), "fn main() {}").unwrap();
+        fs::write(root.join("debug.log"), "noise").unwrap();
+        fs::create_dir_all(root.join("node_modules").join("react")).unwrap();
+        fs::write(
+            root.join("node_modules").join("react").join("index.js"),
+            "x",
+        )
+        .unwrap();
+
+        let rules = ExclusionRules::load(root, &["node_modules/**".to_string()]);
+        let limits = ZipLimits {
+            max_bytes: 0,
+            max_entries: 0,
+        };
+
+        let estimate = estimate_zip_blocking(root, &[root.to_path_buf()], limits, &rules).unwrap();
+        assert_eq!(estimate.files, 2); // main.rs and .oqtoignore
+        assert_eq!(estimate.excluded_files, 2);
+        assert_eq!(estimate.excluded_bytes, 6);
+        assert!(estimate.within_limits);
+
+        let (file, _) =
+            create_zip_tempfile_blocking(root, &[root.to_path_buf()], limits, &rules).unwrap();
+        let mut archive = ZipArchive::new(file).unwrap();
+        assert!(archive.by_name("main.rs").is_ok());
+        assert!(archive.by_name("debug.log").is_err());
+        assert!(archive.by_name("node_modules/react/index.js").is_err());
+
+        let mut manifest = String::new();
+        archive
+            .by_name(MANIFEST_NAME)
+            .unwrap()
+            .read_to_string(&mut manifest)
+            .unwrap();
+        let manifest: serde_json::Value = serde_json::from_str(&manifest).unwrap();
+        assert_eq!(manifest["files"], 2);
+        assert_eq!(manifest["excluded_count"], 2);
+        let mut excluded: Vec<&str> = manifest["excluded"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|e| e["path"].as_str().unwrap())
+            .collect();
+        excluded.sort();
+        assert_eq!(excluded, vec!["debug.log", "node_modules"]);
+    }
+
     #[test]
     fn test_enforce_zip_limits_rejects_too_many_entries() {
         let temp_dir = TempDir::new().unwrap();
@@ -2164,7 +2610,12 @@ mod tests {
             max_entries: 2,
         };
 
-        let result = enforce_zip_limits(root, &[root.to_path_buf()], limits);
+        let result = enforce_zip_limits(
+            root,
+            &[root.to_path_buf()],
+            limits,
+            &ExclusionRules::default(),
+        );
         assert!(matches!(
             result,
             Err(FileServerError::ZipTooManyEntries { .. })
@@ -2184,7 +2635,12 @@ mod tests {
             max_entries: 0,
         };
 
-        let result = enforce_zip_limits(root, &[root.to_path_buf()], limits);
+        let result = enforce_zip_limits(
+            root,
+            &[root.to_path_buf()],
+            limits,
+            &ExclusionRules::default(),
+        );
         assert!(matches!(result, Err(FileServerError::ZipTooLarge { .. })));
     }
 
@@ -2392,11 +2848,22 @@ mod tests {
         let file_remove = EventKind::Remove(RemoveKind::File);
         let dir_remove = EventKind::Remove(RemoveKind::Folder);
 
-        assert_eq!(event_label(&file_create, false), Some("file_created"));
-        assert_eq!(event_label(&dir_create, true), Some("dir_created"));
-        assert_eq!(event_label(&file_modify, false), Some("file_modified"));
-        assert_eq!(event_label(&file_remove, false), Some("file_deleted"));
-        assert_eq!(event_label(&dir_remove, true), Some("dir_deleted"));
-        assert_eq!(event_label(&file_modify, true), None);
+        assert_eq!(event_label(&file_create, false, true), Some("file_created"));
+        assert_eq!(event_label(&dir_create, true, true), Some("dir_created"));
+        assert_eq!(
+            event_label(&file_modify, false, true),
+            Some("file_modified")
+        );
+        assert_eq!(
+            event_label(&file_remove, false, false),
+            Some("file_deleted")
+        );
+        assert_eq!(event_label(&dir_remove, true, false), Some("dir_deleted"));
+        assert_eq!(event_label(&file_modify, true, true), None);
+
+        let rename = EventKind::Modify(ModifyKind::Name(RenameMode::Both));
+        assert_eq!(event_label(&rename, false, false), Some("file_deleted"));
+        assert_eq!(event_label(&rename, false, true), Some("file_created"));
+        assert_eq!(event_label(&rename, true, true), Some("dir_created"));
     }
 }
@@ -3,14 +3,18 @@
 //! This crate provides handlers and routes for serving files from a workspace directory.
 //! It can be used as a standalone binary or embedded in another application.
 
+pub mod archive;
+pub mod capability;
 pub mod config;
 pub mod error;
 pub mod handlers;
 pub mod routes;
+pub mod uploads;
 
 use std::path::PathBuf;
 use std::sync::Arc;
 
+pub use capability::{CapabilityKey, KeySource};
 pub use config::Config;
 pub use error::FileServerError;
 
@@ -21,6 +25,11 @@ pub struct AppState {
     pub root_dir: PathBuf,
     /// Configuration
     pub config: Arc<Config>,
+    /// Key capability tokens are checked against (None = every request is
+    /// refused unless `allow_unauthenticated` is set)
+    pub capability: Option<Arc<KeySource>>,
+    /// Accept requests without a token when no key is configured
+    pub allow_unauthenticated: bool,
 }
 
 impl AppState {
@@ -29,6 +38,8 @@ impl AppState {
         Self {
             root_dir,
             config: Arc::new(Config::default()),
+            capability: None,
+            allow_unauthenticated: false,
         }
     }
 
@@ -37,6 +48,27 @@ impl AppState {
         Self {
             root_dir,
             config: Arc::new(config),
+            capability: None,
+            allow_unauthenticated: false,
         }
     }
+
+    /// Require a capability token signed with `secret` on every request.
+    pub fn with_capability_secret(mut self, secret: &str) -> Self {
+        self.capability = Some(Arc::new(KeySource::Fixed(CapabilityKey::new(secret))));
+        self
+    }
+
+    /// Require a capability token signed with the secret in `path`, re-read
+    /// whenever the file changes.
+    pub fn with_capability_secret_file(mut self, path: PathBuf) -> Self {
+        self.capability = Some(Arc::new(KeySource::file(path)));
+        self
+    }
+
+    /// Accept every request when no secret is configured.
+    pub fn allow_unauthenticated(mut self) -> Self {
+        self.allow_unauthenticated = true;
+        self
+    }
 }
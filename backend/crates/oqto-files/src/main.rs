@@ -3,13 +3,14 @@ use std::path::PathBuf;
 
 use axum::Router;
 use axum::extract::DefaultBodyLimit;
+use axum::middleware;
 use clap::Parser;
 use tower_http::cors::{Any, CorsLayer};
 use tower_http::trace::TraceLayer;
-use tracing::info;
+use tracing::{info, warn};
 use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
 
-use oqto_files::{AppState, Config, routes};
+use oqto_files::{AppState, Config, KeySource, capability, routes};
 
 #[derive(Parser, Debug)]
 #[command(name = "oqto-files")]
@@ -35,6 +36,34 @@ struct Cli {
     /// Config file path (optional)
     #[arg(short, long, env = "OQTO_FILES_CONFIG")]
     config: Option<PathBuf>,
+
+    /// Globs to leave out of zip archives (comma-separated), added to the
+    /// config's `archive_exclude`.
+    #[arg(
+        long,
+        env = "OQTO_FILES_ARCHIVE_EXCLUDE",
+        value_delimiter = ',',
+        num_args = 1..
+    )]
+    archive_exclude: Vec<String>,
+
+    /// Secret capability tokens must be signed with.
+    #[arg(long, env = "OQTO_FILES_TOKEN_SECRET", hide_env_values = true)]
+    token_secret: Option<String>,
+
+    /// File holding the secret capability tokens must be signed with,
+    /// re-read whenever it changes.
+    #[arg(
+        long,
+        env = "OQTO_FILES_TOKEN_SECRET_FILE",
+        conflicts_with = "token_secret"
+    )]
+    token_secret_file: Option<PathBuf>,
+
+    /// Accept requests without a capability token. Only for a fileserver
+    /// nothing but its user can reach.
+    #[arg(long, env = "OQTO_FILES_ALLOW_UNAUTHENTICATED")]
+    allow_unauthenticated: bool,
 }
 
 #[tokio::main]
@@ -56,11 +85,17 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         .init();
 
     // Load config from file if provided, otherwise use defaults
-    let config = if let Some(config_path) = &cli.config {
+    let mut config = if let Some(config_path) = &cli.config {
         Config::from_file(config_path)?
     } else {
         Config::default()
     };
+    config.archive_exclude.extend(
+        cli.archive_exclude
+            .iter()
+            .map(|glob| glob.trim().to_string())
+            .filter(|glob| !glob.is_empty()),
+    );
 
     // Resolve root directory to absolute path
     let root_dir = cli.root.canonicalize().unwrap_or_else(|_| cli.root.clone());
@@ -82,7 +117,27 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         max_upload_size / 1024 / 1024
     );
 
-    let state = AppState::with_config(root_dir, config);
+    let mut state = AppState::with_config(root_dir, config);
+    let token_secret = cli.token_secret.as_deref().filter(|s| !s.is_empty());
+    if let Some(path) = cli.token_secret_file {
+        info!("Capability tokens required (secret in {})", path.display());
+        KeySource::file(path.clone())
+            .key()
+            .map_err(|e| format!("Reading token secret file {}: {e}", path.display()))?;
+        state = state.with_capability_secret_file(path);
+    } else if let Some(secret) = token_secret {
+        info!("Capability tokens required");
+        state = state.with_capability_secret(secret);
+    } else if cli.allow_unauthenticated {
+        warn!("No token secret configured; accepting unauthenticated requests");
+        state = state.allow_unauthenticated();
+    } else {
+        return Err(
+            "No token secret configured: set --token-secret-file or --token-secret, \
+             or pass --allow-unauthenticated"
+                .into(),
+        );
+    }
 
     // Build CORS layer
     let cors = CorsLayer::new()
@@ -93,6 +148,10 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     // Build router
     let app = Router::new()
         .merge(routes::file_routes())
+        .layer(middleware::from_fn_with_state(
+            state.clone(),
+            capability::require_capability,
+        ))
         .layer(DefaultBodyLimit::max(max_upload_size))
         .layer(cors)
         .layer(TraceLayer::new_for_http())
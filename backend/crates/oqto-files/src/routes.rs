@@ -19,6 +19,15 @@ pub fn file_routes() -> Router<AppState> {
         .route("/file", post(handlers::upload_file))
         .route("/file", put(handlers::write_file))
         .route("/file", delete(handlers::delete_file))
+        // Resumable chunked uploads
+        .route("/uploads", post(handlers::create_upload))
+        .route(
+            "/uploads/{id}",
+            get(handlers::get_upload)
+                .patch(handlers::upload_chunk)
+                .delete(handlers::cancel_upload),
+        )
+        .route("/uploads/{id}/complete", post(handlers::complete_upload))
         // Directory operations
         .route("/mkdir", put(handlers::create_dir))
         // Rename/move operations
@@ -26,6 +35,7 @@ pub fn file_routes() -> Router<AppState> {
         // Download operations
         .route("/download", get(handlers::download))
         .route("/download-zip", get(handlers::download_zip))
+        .route("/download-zip/estimate", get(handlers::estimate_zip))
         // Thumbnail generation
         .route("/thumbnail", get(handlers::get_thumbnail))
 }
@@ -0,0 +1,356 @@
+//! Resumable chunked uploads.
+//!
+//! Files too large for one request are uploaded in chunks. `POST /uploads`
+//! reserves an upload of a known size for a destination, `PATCH
+//! /uploads/{id}?offset=N` appends a chunk at the current offset, `GET
+//! /uploads/{id}` reports the offset to resume from after an interruption,
+//! and `POST /uploads/{id}/complete` checks the file and moves it into place.
+//! A chunk may carry its SHA-256 in [`CHUNK_DIGEST_HEADER`]; the whole file
+//! is hashed on completion and compared with the digest given at creation,
+//! if any.
+//!
+//! Uploads are staged in [`STAGING_DIR`] under the request's root, so they
+//! are only found with the same `directory` and the final rename stays on
+//! one filesystem. They survive fileserver restarts; uploads untouched for
+//! [`Config::upload_expiry_secs`] are removed when the next one is created.
+//!
+//! [`Config::upload_expiry_secs`]: crate::Config::upload_expiry_secs
+
+use std::collections::HashMap;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, LazyLock, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use tracing::{debug, warn};
+
+use crate::FileServerError;
+
+/// Directory uploads are staged in, relative to the request's root.
+pub const STAGING_DIR: &str = ".oqto-uploads";
+
+/// Header carrying the hex SHA-256 of a chunk.
+pub const CHUNK_DIGEST_HEADER: &str = "x-chunk-sha256";
+
+/// Chunk size suggested to clients (capped by `max_upload_size`).
+pub const DEFAULT_CHUNK_SIZE: u64 = 8 * 1024 * 1024;
+
+const META_FILE: &str = "upload.json";
+const DATA_FILE: &str = "data";
+
+/// Held while an upload's data is written or checked, so that concurrent
+/// chunks for one upload cannot both pass the offset check.
+static UPLOAD_LOCKS: LazyLock<Mutex<HashMap<PathBuf, Arc<Mutex<()>>>>> =
+    LazyLock::new(|| Mutex::new(HashMap::new()));
+
+fn upload_lock(dir: &Path) -> Arc<Mutex<()>> {
+    let mut locks = UPLOAD_LOCKS.lock().unwrap_or_else(|e| e.into_inner());
+    Arc::clone(locks.entry(dir.to_path_buf()).or_default())
+}
+
+/// An upload in progress, as stored next to its data.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UploadMeta {
+    pub id: String,
+    /// Destination relative to the request's root.
+    pub path: String,
+    /// Total size in bytes.
+    pub size: u64,
+    /// Expected hex SHA-256 of the whole file.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sha256: Option<String>,
+    /// Create missing parent directories on completion.
+    #[serde(default)]
+    pub mkdir: bool,
+    /// Unix seconds.
+    pub created_at: u64,
+}
+
+/// Staged uploads under one root.
+#[derive(Debug, Clone)]
+pub struct UploadStore {
+    dir: PathBuf,
+}
+
+impl UploadStore {
+    pub fn new(root: &Path) -> Self {
+        Self {
+            dir: root.join(STAGING_DIR),
+        }
+    }
+
+    /// Reserve an upload. The id of `meta` is replaced with a fresh one.
+    pub fn create(&self, mut meta: UploadMeta) -> Result<UploadMeta, FileServerError> {
+        if let Some(sha256) = meta.sha256.as_mut() {
+            *sha256 = normalize_digest(sha256)?;
+        }
+        meta.id = new_id();
+        let dir = self.dir.join(&meta.id);
+        fs::create_dir_all(&dir)?;
+        File::create(dir.join(DATA_FILE))?;
+        fs::write(
+            dir.join(META_FILE),
+            serde_json::to_vec(&meta).map_err(io::Error::other)?,
+        )?;
+        Ok(meta)
+    }
+
+    /// An upload and the number of bytes received so far.
+    pub fn load(&self, id: &str) -> Result<(UploadMeta, u64), FileServerError> {
+        let dir = self.upload_dir(id)?;
+        let meta = fs::read(dir.join(META_FILE))
+            .map_err(|_| FileServerError::NotFound(format!("upload {id}")))?;
+        let meta: UploadMeta = serde_json::from_slice(&meta).map_err(io::Error::other)?;
+        let offset = fs::metadata(dir.join(DATA_FILE))?.len();
+        Ok((meta, offset))
+    }
+
+    /// Append a chunk that starts at `offset`; returns the new offset.
+    pub fn append(
+        &self,
+        id: &str,
+        offset: u64,
+        chunk: &[u8],
+        digest: Option<&str>,
+    ) -> Result<u64, FileServerError> {
+        let dir = self.upload_dir(id)?;
+        let lock = upload_lock(&dir);
+        let _guard = lock.lock().unwrap_or_else(|e| e.into_inner());
+        let (meta, current) = self.load(id)?;
+        if offset != current {
+            return Err(FileServerError::UploadOffsetMismatch {
+                expected: current,
+                got: offset,
+            });
+        }
+        let end = current.saturating_add(chunk.len() as u64);
+        if end > meta.size {
+            return Err(FileServerError::FileTooLarge {
+                size: end,
+                limit: meta.size,
+            });
+        }
+        if let Some(digest) = digest {
+            let expected = normalize_digest(digest)?;
+            let actual = hex::encode(Sha256::digest(chunk));
+            if actual != expected {
+                return Err(FileServerError::ChecksumMismatch { expected, actual });
+            }
+        }
+        let mut file = OpenOptions::new().append(true).open(dir.join(DATA_FILE))?;
+        if let Err(e) = file.write_all(chunk).and_then(|()| file.sync_data()) {
+            // Drop a partial write so the offset stays at a chunk boundary.
+            let _ = file.set_len(current);
+            return Err(e.into());
+        }
+        Ok(end)
+    }
+
+    /// Check that an upload is complete and intact; returns the staged data
+    /// file and its SHA-256. The caller moves the file into place.
+    pub fn finish(&self, id: &str) -> Result<(UploadMeta, PathBuf, String), FileServerError> {
+        let dir = self.upload_dir(id)?;
+        let lock = upload_lock(&dir);
+        let _guard = lock.lock().unwrap_or_else(|e| e.into_inner());
+        let (meta, offset) = self.load(id)?;
+        if offset != meta.size {
+            return Err(FileServerError::UploadIncomplete {
+                offset,
+                size: meta.size,
+            });
+        }
+        let data = dir.join(DATA_FILE);
+        let actual = sha256_file(&data)?;
+        if let Some(expected) = meta.sha256.clone()
+            && expected != actual
+        {
+            return Err(FileServerError::ChecksumMismatch { expected, actual });
+        }
+        Ok((meta, data, actual))
+    }
+
+    /// Discard an upload.
+    pub fn remove(&self, id: &str) -> Result<(), FileServerError> {
+        let dir = self.upload_dir(id)?;
+        UPLOAD_LOCKS
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .remove(&dir);
+        match fs::remove_dir_all(&dir) {
+            Ok(()) => Ok(()),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                Err(FileServerError::NotFound(format!("upload {id}")))
+            }
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Remove uploads whose data has not changed for `max_age`.
+    pub fn prune(&self, max_age: Duration) {
+        let Ok(entries) = fs::read_dir(&self.dir) else {
+            return;
+        };
+        for entry in entries.flatten() {
+            // Clock skew can put mtimes in the future; those are fresh.
+            let age = fs::metadata(entry.path().join(DATA_FILE))
+                .and_then(|m| m.modified())
+                .map(|modified| modified.elapsed().unwrap_or_default())
+                .ok();
+            let expired = age.is_none_or(|age| age >= max_age);
+            if expired {
+                debug!("Removing expired upload {:?}", entry.file_name());
+                if let Err(e) = fs::remove_dir_all(entry.path()) {
+                    warn!("Failed to remove expired upload {:?}: {}", entry.path(), e);
+                }
+            }
+        }
+    }
+
+    fn upload_dir(&self, id: &str) -> Result<PathBuf, FileServerError> {
+        if id.len() != 32 || !id.bytes().all(|b| b.is_ascii_hexdigit()) {
+            return Err(FileServerError::NotFound(format!("upload {id}")));
+        }
+        Ok(self.dir.join(id))
+    }
+}
+
+/// Current Unix time in seconds.
+pub fn now_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
+
+fn new_id() -> String {
+    static COUNTER: AtomicU64 = AtomicU64::new(0);
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_nanos())
+        .unwrap_or_default();
+    let mut hasher = Sha256::new();
+    hasher.update(nanos.to_le_bytes());
+    hasher.update(std::process::id().to_le_bytes());
+    hasher.update(COUNTER.fetch_add(1, Ordering::Relaxed).to_le_bytes());
+    hex::encode(&hasher.finalize()[..16])
+}
+
+fn normalize_digest(digest: &str) -> Result<String, FileServerError> {
+    let digest = digest.trim().to_ascii_lowercase();
+    if digest.len() != 64 || !digest.bytes().all(|b| b.is_ascii_hexdigit()) {
+        return Err(FileServerError::InvalidPath(
+            "SHA-256 digest must be 64 hex characters".to_string(),
+        ));
+    }
+    Ok(digest)
+}
+
+fn sha256_file(path: &Path) -> io::Result<String> {
+    let mut file = File::open(path)?;
+    let mut hasher = Sha256::new();
+    let mut buf = vec![0u8; 1024 * 1024];
+    loop {
+        let n = file.read(&mut buf)?;
+        if n == 0 {
+            break;
+        }
+        hasher.update(&buf[..n]);
+    }
+    Ok(hex::encode(hasher.finalize()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn meta(size: u64, sha256: Option<String>) -> UploadMeta {
+        UploadMeta {
+            id: String::new(),
+            path: "data/big.bin".to_string(),
+            size,
+            sha256,
+            mkdir: true,
+            created_at: now_secs(),
+        }
+    }
+
+    #[test]
+    fn test_chunked_upload_resumes_and_verifies() {
+        let root = tempfile::tempdir().unwrap();
+        let store = UploadStore::new(root.path());
+        let body = b"hello resumable world";
+        let digest = hex::encode(Sha256::digest(body));
+        let upload = store
+            .create(meta(body.len() as u64, Some(digest.clone())))
+            .unwrap();
+
+        assert_eq!(store.append(&upload.id, 0, &body[..5], None).unwrap(), 5);
+        // A retried chunk at a stale offset is refused with the real offset.
+        assert!(matches!(
+            store.append(&upload.id, 0, &body[..5], None),
+            Err(FileServerError::UploadOffsetMismatch {
+                expected: 5,
+                got: 0
+            })
+        ));
+        let bad = hex::encode(Sha256::digest(b"other"));
+        assert!(matches!(
+            store.append(&upload.id, 5, &body[5..], Some(&bad)),
+            Err(FileServerError::ChecksumMismatch { .. })
+        ));
+        assert!(matches!(
+            store.finish(&upload.id),
+            Err(FileServerError::UploadIncomplete { offset: 5, .. })
+        ));
+
+        // After a restart the store is rebuilt from disk.
+        let store = UploadStore::new(root.path());
+        let chunk_digest = hex::encode(Sha256::digest(&body[5..]));
+        let end = store
+            .append(&upload.id, 5, &body[5..], Some(&chunk_digest))
+            .unwrap();
+        assert_eq!(end, body.len() as u64);
+        assert!(store.append(&upload.id, end, b"x", None).is_err());
+
+        let (finished, data, sha256) = store.finish(&upload.id).unwrap();
+        assert_eq!(finished.path, "data/big.bin");
+        assert_eq!(sha256, digest);
+        assert_eq!(fs::read(data).unwrap(), body);
+
+        store.remove(&upload.id).unwrap();
+        assert!(matches!(
+            store.load(&upload.id),
+            Err(FileServerError::NotFound(_))
+        ));
+    }
+
+    #[test]
+    fn test_whole_file_digest_and_ids() {
+        let root = tempfile::tempdir().unwrap();
+        let store = UploadStore::new(root.path());
+        let upload = store
+            .create(meta(3, Some(hex::encode(Sha256::digest(b"abc")))))
+            .unwrap();
+        store.append(&upload.id, 0, b"abd", None).unwrap();
+        assert!(matches!(
+            store.finish(&upload.id),
+            Err(FileServerError::ChecksumMismatch { .. })
+        ));
+
+        assert!(store.create(meta(3, Some("nope".to_string()))).is_err());
+        assert!(matches!(
+            store.load("../../etc"),
+            Err(FileServerError::NotFound(_))
+        ));
+
+        // Expiry removes uploads that stopped receiving data.
+        store.prune(Duration::from_secs(3600));
+        assert!(store.load(&upload.id).is_ok());
+        store.prune(Duration::ZERO);
+        assert!(store.load(&upload.id).is_err());
+    }
+}
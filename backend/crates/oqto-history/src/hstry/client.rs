@@ -20,6 +20,24 @@ use hstry_core::service::{ReadServiceClient, WriteServiceClient};
 /// Source ID for Pi sessions (used for deduplication with hstry daemon).
 pub const PI_SOURCE_ID: &str = "pi";
 
+/// Observer called after every hstry write with the operation name, its
+/// duration and whether it succeeded.
+pub type WriteObserver = fn(op: &str, elapsed: std::time::Duration, ok: bool);
+
+static WRITE_OBSERVER: std::sync::OnceLock<WriteObserver> = std::sync::OnceLock::new();
+
+/// Install a process-wide observer for hstry writes (e.g. for metrics).
+/// Only the first call takes effect.
+pub fn set_write_observer(observer: WriteObserver) {
+    let _ = WRITE_OBSERVER.set(observer);
+}
+
+fn observe_write(op: &str, started: std::time::Instant, ok: bool) {
+    if let Some(observer) = WRITE_OBSERVER.get() {
+        observer(op, started.elapsed(), ok);
+    }
+}
+
 /// Explicit endpoint for connecting to a hstry daemon.
 ///
 /// In single-user mode, `Discover` probes the local user's Unix socket and
@@ -156,10 +174,10 @@ impl HstryClient {
             messages,
         };
 
-        let response = client
-            .write_conversation(request)
-            .await
-            .context("Failed to write conversation to hstry")?;
+        let started = std::time::Instant::now();
+        let response = client.write_conversation(request).await;
+        observe_write("write_conversation", started, response.is_ok());
+        let response = response.context("Failed to write conversation to hstry")?;
 
         Ok(response.into_inner())
     }
@@ -228,10 +246,10 @@ impl HstryClient {
             fork_type,
         };
 
-        let response = client
-            .update_conversation(request)
-            .await
-            .context("Failed to update conversation in hstry")?;
+        let started = std::time::Instant::now();
+        let response = client.update_conversation(request).await;
+        observe_write("update_conversation", started, response.is_ok());
+        let response = response.context("Failed to update conversation in hstry")?;
 
         Ok(response.into_inner())
     }
@@ -272,10 +290,10 @@ impl HstryClient {
             external_id: session_id.to_string(),
         };
 
-        let response = client
-            .delete_conversation(request)
-            .await
-            .context("Failed to delete conversation from hstry")?;
+        let started = std::time::Instant::now();
+        let response = client.delete_conversation(request).await;
+        observe_write("delete_conversation", started, response.is_ok());
+        let response = response.context("Failed to delete conversation from hstry")?;
 
         Ok(response.into_inner())
     }
@@ -296,10 +314,10 @@ impl HstryClient {
             updated_at_ms,
         };
 
-        let response = client
-            .append_messages(request)
-            .await
-            .context("Failed to append messages to hstry")?;
+        let started = std::time::Instant::now();
+        let response = client.append_messages(request).await;
+        observe_write("append_messages", started, response.is_ok());
+        let response = response.context("Failed to append messages to hstry")?;
 
         Ok(response.into_inner())
     }
@@ -4,6 +4,6 @@ mod client;
 mod convert;
 mod service;
 
-pub use client::{HstryClient, HstryEndpoint};
+pub use client::{HstryClient, HstryEndpoint, WriteObserver, set_write_observer};
 pub use convert::agent_message_to_proto_with_client_id;
 pub use service::{HstryServiceConfig, HstryServiceManager};
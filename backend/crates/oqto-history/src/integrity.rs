@@ -0,0 +1,200 @@
+//! SQLite integrity checks and snapshot backups.
+//!
+//! Shared by the server (for `oqto.db`) and the runner (for the per-user
+//! hstry database) so both detect corruption the same way and keep
+//! compatible snapshot directories.
+
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use sqlx::sqlite::{SqliteConnectOptions, SqlitePool, SqlitePoolOptions};
+
+/// Maximum number of problems reported by a single check.
+const MAX_REPORTED_PROBLEMS: u32 = 20;
+
+/// Which SQLite consistency check to run.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IntegrityCheck {
+    /// `PRAGMA quick_check`: O(N), skips index/content cross-checks.
+    Quick,
+    /// `PRAGMA integrity_check`: full verification, slower on large DBs.
+    Full,
+}
+
+impl IntegrityCheck {
+    fn pragma(self) -> String {
+        let name = match self {
+            Self::Quick => "quick_check",
+            Self::Full => "integrity_check",
+        };
+        format!("PRAGMA {name}({MAX_REPORTED_PROBLEMS})")
+    }
+}
+
+/// Run a check on an open pool. Returns the reported problems (empty when healthy).
+pub async fn run_check(pool: &SqlitePool, check: IntegrityCheck) -> Result<Vec<String>> {
+    match sqlx::query_scalar::<_, String>(&check.pragma())
+        .fetch_all(pool)
+        .await
+    {
+        Ok(rows) => Ok(rows.into_iter().filter(|row| row != "ok").collect()),
+        // SQLITE_CORRUPT / SQLITE_NOTADB surface as errors rather than rows.
+        Err(sqlx::Error::Database(err)) => Ok(vec![err.message().to_string()]),
+        Err(err) => Err(err).context("running integrity check"),
+    }
+}
+
+/// Check a database file without keeping it open.
+pub async fn check_file(path: &Path, check: IntegrityCheck) -> Result<Vec<String>> {
+    let options = SqliteConnectOptions::new().filename(path).read_only(true);
+    let pool = match SqlitePoolOptions::new()
+        .max_connections(1)
+        .connect_with(options)
+        .await
+    {
+        Ok(pool) => pool,
+        Err(sqlx::Error::Database(err)) => return Ok(vec![err.message().to_string()]),
+        Err(err) => {
+            return Err(err).with_context(|| format!("opening {}", path.display()));
+        }
+    };
+    let result = run_check(&pool, check).await;
+    pool.close().await;
+    result
+}
+
+/// Write a consistent snapshot of `pool` into `dir` and prune old snapshots.
+///
+/// Snapshots are named `<stem>-<UTC timestamp>.db`; at most `keep` are retained.
+pub async fn snapshot(pool: &SqlitePool, dir: &Path, stem: &str, keep: usize) -> Result<PathBuf> {
+    std::fs::create_dir_all(dir)
+        .with_context(|| format!("creating backup directory {}", dir.display()))?;
+
+    let path = dir.join(format!(
+        "{stem}-{}.db",
+        chrono::Utc::now().format("%Y%m%dT%H%M%SZ")
+    ));
+    // VACUUM INTO refuses to overwrite; a second snapshot within the same
+    // second is simply skipped.
+    if path.exists() {
+        return Ok(path);
+    }
+
+    sqlx::query("VACUUM INTO ?")
+        .bind(path.to_string_lossy().as_ref())
+        .execute(pool)
+        .await
+        .with_context(|| format!("writing snapshot {}", path.display()))?;
+
+    for stale in list_snapshots(dir, stem).into_iter().skip(keep.max(1)) {
+        let _ = std::fs::remove_file(stale);
+    }
+    Ok(path)
+}
+
+/// Snapshots for `stem` in `dir`, newest first.
+pub fn list_snapshots(dir: &Path, stem: &str) -> Vec<PathBuf> {
+    let prefix = format!("{stem}-");
+    let mut snapshots: Vec<PathBuf> = std::fs::read_dir(dir)
+        .into_iter()
+        .flatten()
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .filter(|path| {
+            path.file_name()
+                .and_then(|name| name.to_str())
+                .is_some_and(|name| name.starts_with(&prefix) && name.ends_with(".db"))
+        })
+        .collect();
+    // Timestamps sort lexicographically.
+    snapshots.sort();
+    snapshots.reverse();
+    snapshots
+}
+
+/// Most recent snapshot that passes a quick check.
+pub async fn latest_good_snapshot(dir: &Path, stem: &str) -> Option<PathBuf> {
+    for candidate in list_snapshots(dir, stem) {
+        match check_file(&candidate, IntegrityCheck::Quick).await {
+            Ok(problems) if problems.is_empty() => return Some(candidate),
+            Ok(_) | Err(_) => continue,
+        }
+    }
+    None
+}
+
+/// Move a database and its WAL/SHM side files out of the way.
+///
+/// Returns the path the main file was moved to.
+pub fn quarantine(path: &Path) -> Result<PathBuf> {
+    let suffix = format!("corrupt-{}", chrono::Utc::now().format("%Y%m%dT%H%M%SZ"));
+    let target = sibling(path, &suffix);
+    std::fs::rename(path, &target)
+        .with_context(|| format!("moving {} to {}", path.display(), target.display()))?;
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    for side in ["-wal", "-shm"] {
+        let side_path = path.with_file_name(format!("{name}{side}"));
+        if side_path.exists() {
+            let _ = std::fs::rename(&side_path, sibling(&side_path, &suffix));
+        }
+    }
+    Ok(target)
+}
+
+fn sibling(path: &Path, suffix: &str) -> PathBuf {
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    path.with_file_name(format!("{name}.{suffix}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn file_pool(path: &Path) -> SqlitePool {
+        let options = SqliteConnectOptions::new()
+            .filename(path)
+            .create_if_missing(true);
+        SqlitePoolOptions::new()
+            .max_connections(1)
+            .connect_with(options)
+            .await
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn snapshot_and_check_roundtrip() {
+        let dir = tempfile::tempdir().unwrap();
+        let db_path = dir.path().join("test.db");
+        let pool = file_pool(&db_path).await;
+        sqlx::query("CREATE TABLE t (id INTEGER PRIMARY KEY, v TEXT)")
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        assert!(
+            run_check(&pool, IntegrityCheck::Full)
+                .await
+                .unwrap()
+                .is_empty()
+        );
+
+        let backups = dir.path().join("backups");
+        let snap = snapshot(&pool, &backups, "test", 3).await.unwrap();
+        assert!(snap.exists());
+        assert_eq!(list_snapshots(&backups, "test"), vec![snap.clone()]);
+        assert_eq!(latest_good_snapshot(&backups, "test").await, Some(snap));
+    }
+
+    #[tokio::test]
+    async fn garbage_file_reports_problems() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("bad.db");
+        std::fs::write(&path, vec![0x42u8; 8192]).unwrap();
+
+        let problems = check_file(&path, IntegrityCheck::Quick).await.unwrap();
+        assert!(!problems.is_empty());
+
+        let moved = quarantine(&path).unwrap();
+        assert!(!path.exists());
+        assert!(moved.exists());
+    }
+}
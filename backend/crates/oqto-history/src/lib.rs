@@ -5,6 +5,7 @@
 //! crate.
 
 pub mod hstry;
+pub mod integrity;
 pub mod legacy_hstry;
 pub mod oqto_log;
 pub mod session;
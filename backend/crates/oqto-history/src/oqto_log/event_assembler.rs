@@ -571,6 +571,7 @@ mod tests {
             session_id: "ses".to_string(),
             runner_id: "runner".to_string(),
             ts,
+            seq: None,
             payload,
         }
     }
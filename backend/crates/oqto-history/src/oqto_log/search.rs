@@ -21,6 +21,19 @@ pub struct TimelineSearchRequest<'a> {
     pub workspace_id: Option<&'a str>,
     pub cwd: Option<&'a Path>,
     pub limit: usize,
+    pub filters: TimelineSearchFilters<'a>,
+}
+
+/// Narrow a search beyond its scope. Dates are `YYYY-MM-DD` (UTC) and
+/// inclusive.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct TimelineSearchFilters<'a> {
+    /// Session, platform or external ID.
+    pub session_id: Option<&'a str>,
+    /// Sessions in which this agent answered: a `model` or `provider/model`.
+    pub agent: Option<&'a str>,
+    pub since: Option<&'a str>,
+    pub until: Option<&'a str>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -41,10 +54,67 @@ pub struct TimelineSearchResult {
     pub turn_id: String,
     pub message_id: String,
     pub role: String,
+    /// Matched terms in `[` `]`.
     pub snippet: String,
+    /// The snippet split into plain and matched runs.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub snippet_segments: Vec<SnippetSegment>,
     pub content: String,
     pub score: f64,
     pub created_at: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub provider: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub model: Option<String>,
+}
+
+/// Run of snippet text, `highlight`ed when it matched the query.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SnippetSegment {
+    pub text: String,
+    #[serde(default)]
+    pub highlight: bool,
+}
+
+/// Private-use markers around matches in raw snippets. Unlike brackets they
+/// cannot clash with message text.
+const MATCH_OPEN: char = '\u{E000}';
+const MATCH_CLOSE: char = '\u{E001}';
+
+/// Bracketed snippet and its segments from a marked FTS snippet.
+fn split_snippet(raw: &str) -> (String, Vec<SnippetSegment>) {
+    let mut segments = Vec::new();
+    let mut current = SnippetSegment {
+        text: String::new(),
+        highlight: false,
+    };
+    for c in raw.chars() {
+        let highlight = match c {
+            MATCH_OPEN => true,
+            MATCH_CLOSE => false,
+            _ => {
+                current.text.push(c);
+                continue;
+            }
+        };
+        if highlight != current.highlight {
+            let done = std::mem::replace(
+                &mut current,
+                SnippetSegment {
+                    text: String::new(),
+                    highlight,
+                },
+            );
+            if !done.text.is_empty() {
+                segments.push(done);
+            }
+        }
+    }
+    if !current.text.is_empty() {
+        segments.push(current);
+    }
+    let snippet = raw.replace(MATCH_OPEN, "[").replace(MATCH_CLOSE, "]");
+    (snippet, segments)
 }
 
 fn build_fts_query(query: &str) -> String {
@@ -97,38 +167,64 @@ pub async fn search_timeline(req: &TimelineSearchRequest<'_>) -> Result<Timeline
               f.turn_id AS turn_id,
               f.message_id AS message_id,
               f.role AS role,
-              snippet(oqto_log_message_fts, 4, '[', ']', '…', 12) AS snippet,
+              snippet(oqto_log_message_fts, 4, char(57344), char(57345), '…', 12) AS snippet,
               COALESCE(m.content, f.content, '') AS content,
               bm25(oqto_log_message_fts) AS score,
-              m.created_at AS created_at
+              m.created_at AS created_at,
+              json_extract(m.json_payload, '$.provider') AS provider,
+              json_extract(m.json_payload, '$.model') AS model
             FROM oqto_log_message_fts f
             JOIN oqto_log_turns t ON t.turn_id = f.turn_id
             JOIN oqto_log_sessions s ON s.session_id = t.session_id
             LEFT JOIN oqto_log_messages m ON m.message_id = f.message_id
-            WHERE oqto_log_message_fts MATCH ?
+            WHERE oqto_log_message_fts MATCH ?1
+              AND (?2 IS NULL OR ?2 IN (s.session_id, s.platform_id, s.external_id))
+              AND (?3 IS NULL OR EXISTS (
+                SELECT 1
+                FROM oqto_log_turns t2
+                JOIN oqto_log_messages m2 ON m2.turn_id = t2.turn_id
+                WHERE t2.session_id = s.session_id
+                  AND m2.role = 'assistant'
+                  AND (json_extract(m2.json_payload, '$.model') = ?3
+                    OR json_extract(m2.json_payload, '$.provider') || '/'
+                       || json_extract(m2.json_payload, '$.model') = ?3)
+              ))
+              AND (?4 IS NULL OR date(COALESCE(m.created_at, t.created_at)) >= ?4)
+              AND (?5 IS NULL OR date(COALESCE(m.created_at, t.created_at)) <= ?5)
             ORDER BY score ASC
-            LIMIT ?
+            LIMIT ?6
             "#,
         )
         .bind(&fts_query)
+        .bind(req.filters.session_id)
+        .bind(req.filters.agent)
+        .bind(req.filters.since)
+        .bind(req.filters.until)
         .bind(req.limit.max(1) as i64)
         .fetch_all(&pool)
         .await
         .with_context(|| format!("search oqto-log db: {}", db_path.display()))?;
 
-        results.extend(rows.into_iter().map(|row| TimelineSearchResult {
-            session_id: row.try_get("session_id").unwrap_or_default(),
-            platform_id: row.try_get("platform_id").unwrap_or_default(),
-            external_id: row.try_get("external_id").ok(),
-            workspace_id: row.try_get("workspace_id").ok(),
-            branch_id: row.try_get("branch_id").unwrap_or_default(),
-            turn_id: row.try_get("turn_id").unwrap_or_default(),
-            message_id: row.try_get("message_id").unwrap_or_default(),
-            role: row.try_get("role").unwrap_or_default(),
-            snippet: row.try_get("snippet").unwrap_or_default(),
-            content: row.try_get("content").unwrap_or_default(),
-            score: row.try_get("score").unwrap_or(0.0),
-            created_at: row.try_get("created_at").ok(),
+        results.extend(rows.into_iter().map(|row| {
+            let raw_snippet: String = row.try_get("snippet").unwrap_or_default();
+            let (snippet, snippet_segments) = split_snippet(&raw_snippet);
+            TimelineSearchResult {
+                session_id: row.try_get("session_id").unwrap_or_default(),
+                platform_id: row.try_get("platform_id").unwrap_or_default(),
+                external_id: row.try_get("external_id").ok(),
+                workspace_id: row.try_get("workspace_id").ok(),
+                branch_id: row.try_get("branch_id").unwrap_or_default(),
+                turn_id: row.try_get("turn_id").unwrap_or_default(),
+                message_id: row.try_get("message_id").unwrap_or_default(),
+                role: row.try_get("role").unwrap_or_default(),
+                snippet,
+                snippet_segments,
+                content: row.try_get("content").unwrap_or_default(),
+                score: row.try_get("score").unwrap_or(0.0),
+                created_at: row.try_get("created_at").ok(),
+                provider: row.try_get("provider").ok().flatten(),
+                model: row.try_get("model").ok().flatten(),
+            }
         }));
     }
 
@@ -247,6 +343,7 @@ mod tests {
             workspace_id: None,
             cwd: None,
             limit: 10,
+            filters: TimelineSearchFilters::default(),
         })
         .await
         .expect("empty search");
@@ -279,6 +376,7 @@ mod tests {
             workspace_id: None,
             cwd: None,
             limit: 10,
+            filters: TimelineSearchFilters::default(),
         })
         .await
         .expect("search oqto-log");
@@ -319,6 +417,7 @@ mod tests {
             workspace_id: None,
             cwd: None,
             limit: 10,
+            filters: TimelineSearchFilters::default(),
         })
         .await
         .expect("multi-term search");
@@ -329,4 +428,96 @@ mod tests {
         let quoted = build_fts_query("rename \"sessions\"");
         assert_eq!(quoted, "\"rename\" AND \"\"\"sessions\"\"\"");
     }
+
+    #[tokio::test]
+    async fn filters_by_session_agent_and_date() {
+        let temp = tempfile::tempdir().expect("temp home");
+        seed_session(
+            temp.path(),
+            "/tmp/workspace-alpha",
+            "alpha",
+            "needle in alpha",
+        )
+        .await;
+        let mut reply = test_message("assistant", "needle in the beta answer");
+        reply.provider = Some("acme".to_string());
+        reply.model = Some("m1".to_string());
+        append_agent_end_snapshot(
+            temp.path(),
+            "user-1",
+            "/tmp/workspace-beta",
+            "beta",
+            "platform-beta",
+            Some("external-beta"),
+            "external-beta",
+            &[test_message("user", "a question"), reply],
+        )
+        .await
+        .expect("seed oqto-log session");
+
+        let search = |filters: TimelineSearchFilters<'static>| {
+            let user_home = temp.path().to_path_buf();
+            async move {
+                search_timeline(&TimelineSearchRequest {
+                    user_home: &user_home,
+                    query: "needle",
+                    scope: TimelineSearchScope::All,
+                    workspace_id: None,
+                    cwd: None,
+                    limit: 10,
+                    filters,
+                })
+                .await
+                .expect("filtered search")
+                .results
+            }
+        };
+
+        let hits = search(TimelineSearchFilters {
+            session_id: Some("platform-beta"),
+            ..Default::default()
+        })
+        .await;
+        assert_eq!(hits.len(), 1);
+        assert_eq!(hits[0].session_id, "beta");
+        assert_eq!(hits[0].model.as_deref(), Some("m1"));
+
+        let hits = search(TimelineSearchFilters {
+            agent: Some("acme/m1"),
+            ..Default::default()
+        })
+        .await;
+        assert_eq!(hits.len(), 1);
+        assert_eq!(hits[0].session_id, "beta");
+
+        let hits = search(TimelineSearchFilters {
+            until: Some("2000-01-01"),
+            ..Default::default()
+        })
+        .await;
+        assert!(hits.is_empty());
+    }
+
+    #[test]
+    fn snippets_are_split_into_highlighted_runs() {
+        let (snippet, segments) = split_snippet("a [x] \u{E000}needle\u{E001} b");
+        assert_eq!(snippet, "a [x] [needle] b");
+        assert_eq!(
+            segments,
+            vec![
+                SnippetSegment {
+                    text: "a [x] ".to_string(),
+                    highlight: false
+                },
+                SnippetSegment {
+                    text: "needle".to_string(),
+                    highlight: true
+                },
+                SnippetSegment {
+                    text: " b".to_string(),
+                    highlight: false
+                },
+            ]
+        );
+    }
 }
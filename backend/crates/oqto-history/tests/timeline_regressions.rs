@@ -49,6 +49,7 @@ fn event(ts: i64, payload: EventPayload) -> Event {
         session_id: "oqto-session".to_string(),
         runner_id: "runner".to_string(),
         ts,
+        seq: None,
         payload,
     }
 }
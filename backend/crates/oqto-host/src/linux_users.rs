@@ -4,7 +4,7 @@
 //! platform users, enabling proper process isolation in multi-user deployments.
 
 use anyhow::{Context, Result};
-use log::{debug, info};
+use log::{debug, info, warn};
 use rustix::process::{geteuid, getuid};
 use serde::{Deserialize, Serialize};
 
@@ -36,6 +36,10 @@ pub struct LinuxUsersConfig {
     /// When enabled, runtime code must use persisted `linux_username`/`linux_uid`
     /// from the users table and reject legacy fallbacks.
     pub strict_identity: bool,
+    /// Disk quota applied to each new user's home filesystem, in GiB.
+    /// 0 disables quotas. Requires user quotas on the filesystem holding
+    /// /home and the oqto-usermgr daemon.
+    pub quota_gb: u64,
 }
 
 impl Default for LinuxUsersConfig {
@@ -49,10 +53,22 @@ impl Default for LinuxUsersConfig {
             use_sudo: true,
             create_home: true,
             strict_identity: false,
+            quota_gb: 0,
         }
     }
 }
 
+/// Disk usage and quota of one Linux user, as reported by oqto-usermgr.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DiskUsage {
+    /// Linux username.
+    pub username: String,
+    pub used_bytes: u64,
+    /// 0 when no limit is set.
+    pub soft_limit_bytes: u64,
+    pub hard_limit_bytes: u64,
+}
+
 /// Prefix for project-based Linux users.
 const PROJECT_PREFIX: &str = "proj_";
 
@@ -493,9 +509,43 @@ impl LinuxUsersConfig {
             .with_context(|| format!("creating user '{}'", username))?;
 
         info!("Created Linux user '{}' with UID {}", username, uid);
+
+        if self.quota_gb > 0 {
+            // A missing quota should not block provisioning; admins see
+            // unlimited users in the disk usage report.
+            if let Err(e) = self.set_quota(username, self.quota_gb) {
+                warn!(
+                    "Failed to apply {} GiB quota to '{}': {:#}",
+                    self.quota_gb, username, e
+                );
+            }
+        }
+
         Ok((uid, username.to_string()))
     }
 
+    /// Limit a Linux user's disk usage to `gb` GiB (0 removes the limit).
+    pub fn set_quota(&self, linux_username: &str, gb: u64) -> Result<()> {
+        usermgr_request(
+            "set-quota",
+            serde_json::json!({ "username": linux_username, "gb": gb }),
+        )?;
+        info!("Set {} GiB quota for '{}'", gb, linux_username);
+        Ok(())
+    }
+
+    /// Disk usage and quotas of all platform Linux users on the filesystem
+    /// holding /home.
+    pub fn disk_usage(&self) -> Result<Vec<DiskUsage>> {
+        let data = usermgr_request_with_data("quota-report", serde_json::json!({}))?
+            .context("oqto-usermgr quota-report returned no data")?;
+        let users = data
+            .get("users")
+            .cloned()
+            .unwrap_or(serde_json::Value::Array(Vec::new()));
+        serde_json::from_value(users).context("parsing quota report")
+    }
+
     /// Ensure a Linux user exists, creating it if necessary.
     /// Returns (UID, actual_linux_username).
     ///
@@ -1524,6 +1574,8 @@ mod tests {
             shell: "/bin/zsh".to_string(),
             use_sudo: false,
             create_home: false,
+            quota_gb: 10,
+            ..Default::default()
         };
 
         let json = serde_json::to_string(&config).unwrap();
@@ -1536,5 +1588,6 @@ mod tests {
         assert_eq!(parsed.shell, config.shell);
         assert_eq!(parsed.use_sudo, config.use_sudo);
         assert_eq!(parsed.create_home, config.create_home);
+        assert_eq!(parsed.quota_gb, config.quota_gb);
     }
 }
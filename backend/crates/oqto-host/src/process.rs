@@ -666,19 +666,34 @@ impl ProcessManager {
     /// Returns a list of (service_name, exit_reason) for processes that have exited.
     /// Returns empty vec if all processes are running or session doesn't exist.
     pub async fn get_session_exit_info(&self, session_id: &str) -> Vec<(String, String)> {
+        self.get_session_exit_statuses(session_id)
+            .await
+            .into_iter()
+            .map(|(service, code, signal)| {
+                (service, ProcessHandle::format_exit_status(code, signal))
+            })
+            .collect()
+    }
+
+    /// Get the raw exit status of any crashed processes in a session.
+    ///
+    /// Returns a list of (service_name, exit_code, signal) for processes that have exited.
+    pub async fn get_session_exit_statuses(
+        &self,
+        session_id: &str,
+    ) -> Vec<(String, Option<i32>, Option<i32>)> {
         let mut processes = self.processes.lock().await;
-        let mut exit_info = Vec::new();
+        let mut statuses = Vec::new();
 
         if let Some(handles) = processes.get_mut(session_id) {
             for handle in handles.iter_mut() {
                 if let Some((code, signal)) = handle.check_exit_status() {
-                    let reason = ProcessHandle::format_exit_status(code, signal);
-                    exit_info.push((handle.service.clone(), reason));
+                    statuses.push((handle.service.clone(), code, signal));
                 }
             }
         }
 
-        exit_info
+        statuses
     }
 
     /// Get the list of PIDs for a session.
@@ -298,6 +298,18 @@ impl LocalRuntime {
         self.process_manager.get_session_exit_info(session_id).await
     }
 
+    /// Get the raw exit status of crashed processes in a session.
+    ///
+    /// Returns a list of (service_name, exit_code, signal) for processes that have exited.
+    pub async fn get_session_exit_statuses(
+        &self,
+        session_id: &str,
+    ) -> Vec<(String, Option<i32>, Option<i32>)> {
+        self.process_manager
+            .get_session_exit_statuses(session_id)
+            .await
+    }
+
     /// Get the state of a session (similar to container state).
     #[allow(dead_code)]
     pub async fn get_session_state(&self, session_id: &str) -> Option<String> {
@@ -806,6 +818,7 @@ mod tests {
                 shell: "/bin/zsh".to_string(),
                 use_sudo: false,
                 create_home: false,
+                ..Default::default()
             },
             ..Default::default()
         };
@@ -849,6 +862,7 @@ mod tests {
                 shell: "/bin/sh".to_string(),
                 use_sudo: true,
                 create_home: true,
+                ..Default::default()
             },
             sandbox: None,
             cleanup_on_startup: false,
@@ -41,7 +41,15 @@ pub enum CommandPayload {
     // -- Session lifecycle --
     /// Create a new agent session.
     #[serde(rename = "session.create")]
-    SessionCreate { config: SessionConfig },
+    SessionCreate {
+        config: SessionConfig,
+        /// Last event `seq` the client saw for this session before it lost
+        /// its connection. Events after it that the backend still holds are
+        /// replayed before live events; if some were evicted the client gets
+        /// `stream.resync_required` instead.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        last_seen_seq: Option<u64>,
+    },
 
     /// Close/destroy the session.
     #[serde(rename = "session.close")]
@@ -96,6 +104,16 @@ pub enum CommandPayload {
         client_id: Option<String>,
     },
 
+    /// Guidance for the agent mid-turn. Applied live when the harness takes
+    /// steering messages, otherwise queued for the start of the next turn
+    /// (see `agent.interjection`).
+    Interject {
+        message: String,
+        /// Client-generated ID for optimistic message matching.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        client_id: Option<String>,
+    },
+
     /// Abort current operation.
     Abort,
 
@@ -206,6 +224,12 @@ pub struct SessionConfig {
     /// Resume from existing session file.
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub continue_session: Option<String>,
+
+    /// Sandbox profile ("net-off", "read-only-home", "full" or one defined
+    /// in the runner's sandbox.toml). It can only add restrictions to the
+    /// runner's sandbox.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sandbox_profile: Option<String>,
 }
 
 /// Image attachment for prompts.
@@ -253,7 +277,9 @@ mod tests {
                     provider: Some("anthropic".to_string()),
                     model: None,
                     continue_session: None,
+                    sandbox_profile: None,
                 },
+                last_seen_seq: None,
             },
         };
 
@@ -263,7 +289,7 @@ mod tests {
 
         let parsed: Command = serde_json::from_str(&json).unwrap();
         match &parsed.payload {
-            CommandPayload::SessionCreate { config } => {
+            CommandPayload::SessionCreate { config, .. } => {
                 assert_eq!(config.harness, "pi");
                 assert_eq!(config.provider.as_deref(), Some("anthropic"));
             }
@@ -9,6 +9,8 @@
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 
+use crate::Part;
+use crate::canon::MediaDimensions;
 use crate::delegation::{DelegateCompleted, DelegateDelta, DelegateError, DelegateStarted};
 use crate::messages::{Message, StopReason};
 
@@ -28,6 +30,12 @@ pub struct Event {
     /// Unix ms timestamp.
     pub ts: i64,
 
+    /// Position in the session's replay log. Assigned by the backend when it
+    /// forwards the event to clients; a reconnecting client passes the last
+    /// one it saw to get the events it missed (see [`crate::replay`]).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub seq: Option<u64>,
+
     /// The event payload.
     #[serde(flatten)]
     pub payload: EventPayload,
@@ -73,6 +81,12 @@ pub enum EventPayload {
         message_version: Option<MessageVersion>,
     },
 
+    /// An agent run finished: the turn started by a prompt, retries
+    /// included, ended and the agent went idle. Emitted once per run, after
+    /// that run's `agent.idle`.
+    #[serde(rename = "agent.run_completed")]
+    AgentRunCompleted { run_id: String },
+
     /// Agent is working (LLM generating, tool running, etc.).
     #[serde(rename = "agent.working")]
     AgentWorking {
@@ -90,6 +104,20 @@ pub enum EventPayload {
         phase: Option<AgentPhase>,
     },
 
+    /// Guidance sent with an `interject` command was accepted.
+    #[serde(rename = "agent.interjection")]
+    AgentInterjection {
+        interjection_id: String,
+        message: String,
+        delivery: InterjectionDelivery,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        client_id: Option<String>,
+    },
+
+    /// Queued interjections were handed to the agent as a new turn.
+    #[serde(rename = "agent.interjection_delivered")]
+    AgentInterjectionDelivered { interjection_ids: Vec<String> },
+
     /// Agent needs user input.
     #[serde(rename = "agent.input_needed")]
     AgentInputNeeded { request: InputRequest },
@@ -150,6 +178,17 @@ pub enum EventPayload {
     #[serde(rename = "stream.message_end")]
     StreamMessageEnd { message: Message },
 
+    /// Output lint hooks checked a finished assistant message (the one
+    /// streamed as `message_id`). When `blocked` is set its text was
+    /// replaced before it was stored, so clients should drop the streamed
+    /// text in favour of `stream.message_end`.
+    #[serde(rename = "stream.message_lint")]
+    StreamMessageLint {
+        message_id: String,
+        blocked: bool,
+        findings: Vec<LintFinding>,
+    },
+
     /// Stream complete.
     #[serde(rename = "stream.done")]
     StreamDone { reason: StopReason },
@@ -172,6 +211,23 @@ pub enum EventPayload {
         partial_output: Value,
     },
 
+    /// Incremental tool output, in place of `tool.progress` for tools that
+    /// stream text (e.g. `bash`). Appending `delta`s in `seq` order rebuilds
+    /// the output; the final result still arrives in `tool.end`.
+    #[serde(rename = "tool.output_delta")]
+    ToolOutputDelta {
+        tool_call_id: String,
+        name: String,
+        channel: OutputChannel,
+        /// Chunk number within the tool call, from 0 across both channels.
+        seq: u64,
+        delta: String,
+        /// The runner's per-call cap was reached; this is the last chunk and
+        /// further output is only in `tool.end`.
+        #[serde(default)]
+        truncated: bool,
+    },
+
     /// Tool completed.
     #[serde(rename = "tool.end")]
     ToolEnd {
@@ -183,6 +239,49 @@ pub enum EventPayload {
         duration_ms: Option<u64>,
     },
 
+    /// A tool call exceeded the session's rate limit for its tool class. The
+    /// runner has asked the agent to back off for `retry_after_ms`; when
+    /// `aborted` is set the agent kept going and its turn was aborted.
+    #[serde(rename = "tool.rate_limited")]
+    ToolRateLimited {
+        tool_call_id: String,
+        name: String,
+        /// Rate-limit rule the tool matched (e.g. `bash`, `network`).
+        rule: String,
+        max_calls: u32,
+        window_secs: u64,
+        retry_after_ms: u64,
+        #[serde(default)]
+        aborted: bool,
+    },
+
+    /// A tool call matched an approval rule and is paused until a user
+    /// approves or denies it (or `expires_at` passes, which denies it).
+    #[serde(rename = "tool.approval_required")]
+    ToolApprovalRequired {
+        approval_id: String,
+        tool_call_id: String,
+        name: String,
+        input: Value,
+        /// Approval rule the call matched (e.g. `git-push`).
+        rule: String,
+        /// Unix ms.
+        expires_at: i64,
+    },
+
+    /// A paused tool call was approved or denied.
+    #[serde(rename = "tool.approval_resolved")]
+    ToolApprovalResolved {
+        approval_id: String,
+        tool_call_id: String,
+        approved: bool,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        reason: Option<String>,
+        /// Nobody answered in time; the call was denied.
+        #[serde(default)]
+        timed_out: bool,
+    },
+
     // -- Auto-recovery --
     /// Auto-retry starting.
     #[serde(rename = "retry.start")]
@@ -230,6 +329,67 @@ pub enum EventPayload {
     #[serde(rename = "config.thinking_level_changed")]
     ConfigThinkingLevelChanged { level: String },
 
+    // -- Budget --
+    /// The session's spend reached the warning share of its budget.
+    /// Emitted by the backend.
+    #[serde(rename = "budget.warning")]
+    BudgetWarning { spent_usd: f64, limit_usd: f64 },
+
+    /// The session's spend reached its budget. With `suspended`, the agent
+    /// was aborted and new prompts are refused until the budget is raised.
+    /// Emitted by the backend.
+    #[serde(rename = "budget.exceeded")]
+    BudgetExceeded {
+        spent_usd: f64,
+        limit_usd: f64,
+        suspended: bool,
+    },
+
+    // -- Resources --
+    /// The OOM killer killed processes of the session at its memory cap.
+    /// `oom_kills` counts the kills since the session started. Emitted by
+    /// the runner, which caps agents with cgroup v2 in local mode.
+    #[serde(rename = "resource.oom_killed")]
+    ResourceOomKilled {
+        oom_kills: u64,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        memory_limit_mb: Option<u64>,
+    },
+
+    // -- Network --
+    /// The egress policy blocked a connection of the session. `host` is the
+    /// domain the agent asked for, when it could be read. Emitted by the
+    /// runner in local mode and by the backend in container mode.
+    #[serde(rename = "network.blocked")]
+    NetworkBlocked {
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        host: Option<String>,
+        address: String,
+        port: u16,
+        reason: String,
+    },
+
+    // -- Image builds --
+    /// A container image build from a Dockerfile in the session's workspace
+    /// started. Emitted by the backend.
+    #[serde(rename = "image.build_started")]
+    ImageBuildStarted { build_id: String, image: String },
+
+    /// A line of output of the image build.
+    #[serde(rename = "image.build_output")]
+    ImageBuildOutput { build_id: String, line: String },
+
+    /// The image build finished. With `success`, new sessions can be
+    /// started from `image`.
+    #[serde(rename = "image.build_finished")]
+    ImageBuildFinished {
+        build_id: String,
+        image: String,
+        success: bool,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        error: Option<String>,
+    },
+
     // -- Notifications --
     /// Extension-originated notification.
     Notify { level: NotifyLevel, message: String },
@@ -248,6 +408,14 @@ pub enum EventPayload {
     /// Persisted count (after hstry write).
     Persisted { message_count: u64 },
 
+    // -- Artifacts --
+    /// The agent wrote a file to the session's artifacts directory, or a
+    /// workspace file matching the runner's artifact globs. `artifact.part`
+    /// is an image part that can be rendered inline, or an attachment part
+    /// for other files.
+    #[serde(rename = "artifact.created")]
+    ArtifactCreated { artifact: MediaArtifact },
+
     // -- Transport reliability --
     /// The event stream lagged and events were dropped. The client should
     /// refetch state and messages to rebuild its timeline from scratch.
@@ -286,6 +454,52 @@ pub enum EventPayload {
 // Supporting types
 // ============================================================================
 
+/// File captured from a session's artifacts directory or workspace.
+///
+/// The file is copied out of the workspace when it is captured, so `url`
+/// keeps pointing at the same bytes even if the agent overwrites or deletes
+/// the original.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MediaArtifact {
+    /// Artifact ID (unique per runner).
+    pub id: String,
+    /// File name as written by the agent.
+    pub filename: String,
+    pub mime_type: String,
+    pub size_bytes: u64,
+    /// Pixel size, for raster images.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub dimensions: Option<MediaDimensions>,
+    /// Backend URL serving the captured file.
+    pub url: String,
+    /// Backend URL serving a small preview.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub thumbnail_url: Option<String>,
+    /// Tool call that produced the file, if known.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tool_call_id: Option<String>,
+    /// Path relative to the session's working directory, for workspace
+    /// files matched by an artifact glob.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub path: Option<String>,
+    /// When the runner captured the file (Unix ms).
+    pub created_at: i64,
+    /// When the runner deletes the copy (Unix ms), if artifacts expire.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expires_at: Option<i64>,
+    /// Canonical image or attachment part referencing `url`.
+    pub part: Part,
+}
+
+/// Backend URL for a captured artifact (`thumbnail` selects the preview).
+pub fn artifact_url(session_id: &str, artifact_id: &str, thumbnail: bool) -> String {
+    let mut url = format!("/api/sessions/{session_id}/artifacts/{artifact_id}");
+    if thumbnail {
+        url.push_str("/thumbnail");
+    }
+    url
+}
+
 /// Monotonic message version from the persistence layer.
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct MessageVersion {
@@ -413,6 +627,37 @@ pub struct ToolCallInfo {
     pub input: Value,
 }
 
+/// Result of one output lint hook for a message.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LintFinding {
+    /// Name of the hook.
+    pub hook: String,
+    pub verdict: LintVerdict,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub message: Option<String>,
+    pub duration_ms: u64,
+}
+
+/// What an output lint hook decided.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LintVerdict {
+    /// The message is delivered with a note.
+    Annotate,
+    /// The message text is withheld.
+    Block,
+    /// The hook failed or ran out of time and was skipped.
+    Bypassed,
+}
+
+/// Output stream of a running tool.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OutputChannel {
+    Stdout,
+    Stderr,
+}
+
 /// Reason for compaction.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 #[serde(rename_all = "snake_case")]
@@ -421,6 +666,16 @@ pub enum CompactReason {
     Overflow,
 }
 
+/// How an interjection reaches the agent.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum InterjectionDelivery {
+    /// Steered into the running turn.
+    Live,
+    /// Held until the current turn ends.
+    Queued,
+}
+
 /// Notification severity level.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 #[serde(rename_all = "lowercase")]
@@ -470,6 +725,7 @@ mod tests {
             session_id: "ses_abc".to_string(),
             runner_id: "local".to_string(),
             ts: 1738764000000,
+            seq: None,
             payload: EventPayload::AgentWorking {
                 phase: AgentPhase::Generating,
                 detail: None,
@@ -482,12 +738,49 @@ mod tests {
         assert!(json.contains("\"session_id\":\"ses_abc\""));
     }
 
+    #[test]
+    fn test_artifact_created_serialization() {
+        let url = artifact_url("ses_abc", "art_1", false);
+        let event = Event {
+            session_id: "ses_abc".to_string(),
+            runner_id: "local".to_string(),
+            ts: 1738764000000,
+            seq: None,
+            payload: EventPayload::ArtifactCreated {
+                artifact: MediaArtifact {
+                    id: "art_1".to_string(),
+                    filename: "plot.png".to_string(),
+                    mime_type: "image/png".to_string(),
+                    size_bytes: 1024,
+                    dimensions: Some(MediaDimensions::new(640, 480)),
+                    url: url.clone(),
+                    thumbnail_url: Some(artifact_url("ses_abc", "art_1", true)),
+                    tool_call_id: None,
+                    path: None,
+                    created_at: 1738764000000,
+                    expires_at: None,
+                    part: Part::Image {
+                        id: "part_1".to_string(),
+                        source: crate::MediaSource::url(url.as_str()),
+                        alt: Some("plot.png".to_string()),
+                    },
+                },
+            },
+        };
+
+        let json = serde_json::to_string(&event).unwrap();
+        assert!(json.contains("\"event\":\"artifact.created\""));
+        assert!(json.contains("\"url\":\"/api/sessions/ses_abc/artifacts/art_1\""));
+        assert!(json.contains("/api/sessions/ses_abc/artifacts/art_1/thumbnail"));
+    }
+
     #[test]
     fn test_stream_text_delta() {
         let event = Event {
             session_id: "ses_abc".to_string(),
             runner_id: "local".to_string(),
             ts: 1738764000000,
+            seq: None,
             payload: EventPayload::StreamTextDelta {
                 message_id: "msg-1".to_string(),
                 delta: "Hello".to_string(),
@@ -500,6 +793,51 @@ mod tests {
         assert!(json.contains("\"delta\":\"Hello\""));
     }
 
+    #[test]
+    fn test_tool_output_delta() {
+        let payload = EventPayload::ToolOutputDelta {
+            tool_call_id: "call_1".to_string(),
+            name: "bash".to_string(),
+            channel: OutputChannel::Stderr,
+            seq: 3,
+            delta: "warning: unused\n".to_string(),
+            truncated: false,
+        };
+
+        let json = serde_json::to_value(&payload).unwrap();
+        assert_eq!(json["event"], "tool.output_delta");
+        assert_eq!(json["channel"], "stderr");
+        assert_eq!(json["seq"], 3);
+
+        let parsed: EventPayload = serde_json::from_str(
+            r#"{"event":"tool.output_delta","tool_call_id":"call_1","name":"bash","channel":"stdout","seq":0,"delta":"ok"}"#,
+        )
+        .unwrap();
+        assert!(matches!(
+            parsed,
+            EventPayload::ToolOutputDelta {
+                channel: OutputChannel::Stdout,
+                truncated: false,
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    fn test_interjection_serialization() {
+        let payload = EventPayload::AgentInterjection {
+            interjection_id: "int-1".to_string(),
+            message: "use the staging database".to_string(),
+            delivery: InterjectionDelivery::Queued,
+            client_id: None,
+        };
+
+        let json = serde_json::to_value(&payload).unwrap();
+        assert_eq!(json["event"], "agent.interjection");
+        assert_eq!(json["delivery"], "queued");
+        assert!(json.get("client_id").is_none());
+    }
+
     #[test]
     fn test_command_response_event_serialization() {
         // Verify that CommandResponse fields are flattened into the event
@@ -509,6 +847,7 @@ mod tests {
             session_id: "ses_abc".to_string(),
             runner_id: "local".to_string(),
             ts: 1738764000000,
+            seq: None,
             payload: EventPayload::Response(CommandResponse {
                 id: "req-1".to_string(),
                 cmd: "session.create".to_string(),
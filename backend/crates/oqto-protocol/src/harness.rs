@@ -0,0 +1,344 @@
+//! Agent harness manifests.
+//!
+//! A harness is the agent process a runner spawns for a session. Each one is
+//! declared by a manifest (usually a TOML file in the runner's harness
+//! directory) giving its binary, an argument template, extra environment and
+//! the adapter that translates its native output into canonical events:
+//!
+//! ```toml
+//! name = "pi-nightly"
+//! description = "Pi from the nightly channel"
+//! binary = "/opt/pi-nightly/bin/pi"
+//! args = ["--mode", "rpc", "--approve", ["--model", "{model}"], ["--session", "{session_file}"]]
+//! adapter = "pi"
+//!
+//! [env]
+//! PI_TELEMETRY = "0"
+//! ```
+//!
+//! Arguments may use the placeholders in [`PLACEHOLDERS`]. A nested array is
+//! a group that is left out entirely when any placeholder in it has no value,
+//! so optional flags stay together with their values.
+
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+/// Name of the harness sessions use when they do not pick one.
+pub const DEFAULT_HARNESS: &str = "pi";
+
+/// Placeholders available in argument templates.
+pub const PLACEHOLDERS: &[&str] = &["session_id", "cwd", "provider", "model", "session_file"];
+
+/// Translator for a harness's native protocol.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum HarnessAdapter {
+    /// Pi RPC mode: JSON commands on stdin, JSONL events on stdout.
+    #[default]
+    Pi,
+}
+
+/// One argument, or a group of arguments that is kept or dropped together.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum ArgTemplate {
+    Arg(String),
+    Group(Vec<String>),
+}
+
+/// Declaration of an agent harness.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HarnessManifest {
+    /// Name sessions select the harness by (`SessionConfig::harness`).
+    pub name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    /// Executable to spawn (path or name on `PATH`).
+    pub binary: String,
+    /// Argument template.
+    #[serde(default)]
+    pub args: Vec<ArgTemplate>,
+    /// Environment for the process. Session environment takes precedence.
+    #[serde(default)]
+    pub env: HashMap<String, String>,
+    #[serde(default)]
+    pub adapter: HarnessAdapter,
+    /// Whether the harness takes steering messages mid-turn. Interjections
+    /// to a harness without steering wait until its turn ends.
+    #[serde(default = "default_steering")]
+    pub steering: bool,
+}
+
+fn default_steering() -> bool {
+    true
+}
+
+/// Values for the placeholders of one launch.
+#[derive(Debug, Clone, Default)]
+pub struct HarnessLaunch {
+    pub session_id: String,
+    pub cwd: String,
+    pub provider: Option<String>,
+    pub model: Option<String>,
+    pub session_file: Option<String>,
+}
+
+impl HarnessLaunch {
+    fn value(&self, placeholder: &str) -> Option<&str> {
+        match placeholder {
+            "session_id" => Some(&self.session_id),
+            "cwd" => Some(&self.cwd),
+            "provider" => self.provider.as_deref(),
+            "model" => self.model.as_deref(),
+            "session_file" => self.session_file.as_deref(),
+            _ => None,
+        }
+        .filter(|value| !value.is_empty())
+    }
+}
+
+/// Invalid harness manifest.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum HarnessError {
+    /// Name is empty or not `[A-Za-z0-9_.-]`.
+    InvalidName(String),
+    MissingBinary(String),
+    /// Template references a placeholder that does not exist.
+    UnknownPlaceholder {
+        harness: String,
+        placeholder: String,
+    },
+    /// Unterminated `{`.
+    MalformedTemplate {
+        harness: String,
+        arg: String,
+    },
+}
+
+impl fmt::Display for HarnessError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::InvalidName(name) => write!(f, "invalid harness name '{name}'"),
+            Self::MissingBinary(name) => write!(f, "harness '{name}' has no binary"),
+            Self::UnknownPlaceholder {
+                harness,
+                placeholder,
+            } => write!(
+                f,
+                "harness '{harness}' uses unknown placeholder '{{{placeholder}}}'"
+            ),
+            Self::MalformedTemplate { harness, arg } => {
+                write!(f, "harness '{harness}' has a malformed argument '{arg}'")
+            }
+        }
+    }
+}
+
+impl std::error::Error for HarnessError {}
+
+/// Split an argument into literal text and placeholders.
+fn segments(arg: &str) -> Option<Vec<(bool, &str)>> {
+    let mut out = Vec::new();
+    let mut rest = arg;
+    while let Some(start) = rest.find('{') {
+        if start > 0 {
+            out.push((false, &rest[..start]));
+        }
+        let end = rest[start..].find('}')? + start;
+        out.push((true, &rest[start + 1..end]));
+        rest = &rest[end + 1..];
+    }
+    if !rest.is_empty() {
+        out.push((false, rest));
+    }
+    Some(out)
+}
+
+/// Substitute placeholders, or None when one has no value.
+fn render(arg: &str, launch: &HarnessLaunch) -> Option<String> {
+    let mut out = String::new();
+    for (is_placeholder, text) in segments(arg)? {
+        if is_placeholder {
+            out.push_str(launch.value(text)?);
+        } else {
+            out.push_str(text);
+        }
+    }
+    Some(out)
+}
+
+impl HarnessManifest {
+    /// The built-in Pi harness, spawned in RPC mode.
+    pub fn pi(binary: impl Into<String>) -> Self {
+        let group = |flag: &str, value: &str| {
+            ArgTemplate::Group(vec![flag.to_string(), format!("{{{value}}}")])
+        };
+        Self {
+            name: DEFAULT_HARNESS.to_string(),
+            description: Some("Pi coding agent".to_string()),
+            binary: binary.into(),
+            args: vec![
+                ArgTemplate::Arg("--mode".to_string()),
+                ArgTemplate::Arg("rpc".to_string()),
+                ArgTemplate::Arg("--approve".to_string()),
+                group("--provider", "provider"),
+                group("--model", "model"),
+                group("--session", "session_file"),
+            ],
+            env: HashMap::new(),
+            adapter: HarnessAdapter::Pi,
+            steering: true,
+        }
+    }
+
+    /// Check the name, binary and argument template.
+    pub fn validate(&self) -> Result<(), HarnessError> {
+        let valid_name = !self.name.is_empty()
+            && self
+                .name
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '.'));
+        if !valid_name {
+            return Err(HarnessError::InvalidName(self.name.clone()));
+        }
+        if self.binary.trim().is_empty() {
+            return Err(HarnessError::MissingBinary(self.name.clone()));
+        }
+        for arg in self.args.iter().flat_map(|template| match template {
+            ArgTemplate::Arg(arg) => std::slice::from_ref(arg),
+            ArgTemplate::Group(args) => args.as_slice(),
+        }) {
+            let parts = segments(arg).ok_or_else(|| HarnessError::MalformedTemplate {
+                harness: self.name.clone(),
+                arg: arg.clone(),
+            })?;
+            if let Some((_, placeholder)) = parts
+                .iter()
+                .find(|(is_placeholder, text)| *is_placeholder && !PLACEHOLDERS.contains(text))
+            {
+                return Err(HarnessError::UnknownPlaceholder {
+                    harness: self.name.clone(),
+                    placeholder: placeholder.to_string(),
+                });
+            }
+        }
+        Ok(())
+    }
+
+    /// Arguments for a launch. Single arguments with an unset placeholder and
+    /// groups with any unset placeholder are left out.
+    pub fn render_args(&self, launch: &HarnessLaunch) -> Vec<String> {
+        let mut out = Vec::new();
+        for template in &self.args {
+            match template {
+                ArgTemplate::Arg(arg) => out.extend(render(arg, launch)),
+                ArgTemplate::Group(args) => {
+                    if let Some(rendered) = args
+                        .iter()
+                        .map(|arg| render(arg, launch))
+                        .collect::<Option<Vec<_>>>()
+                    {
+                        out.extend(rendered);
+                    }
+                }
+            }
+        }
+        out
+    }
+}
+
+/// Harnesses a runner can spawn, by name.
+#[derive(Debug, Clone, Default)]
+pub struct HarnessRegistry {
+    harnesses: BTreeMap<String, HarnessManifest>,
+}
+
+impl HarnessRegistry {
+    /// Registry holding the built-in Pi harness.
+    pub fn with_pi(pi_binary: impl Into<String>) -> Self {
+        let mut registry = Self::default();
+        registry
+            .harnesses
+            .insert(DEFAULT_HARNESS.to_string(), HarnessManifest::pi(pi_binary));
+        registry
+    }
+
+    /// Add a harness after validating it. Returns the manifest it replaced
+    /// (a manifest named `pi` overrides the built-in one).
+    pub fn register(
+        &mut self,
+        manifest: HarnessManifest,
+    ) -> Result<Option<HarnessManifest>, HarnessError> {
+        manifest.validate()?;
+        Ok(self.harnesses.insert(manifest.name.clone(), manifest))
+    }
+
+    /// Harness by name; None selects [`DEFAULT_HARNESS`].
+    pub fn get(&self, name: Option<&str>) -> Option<&HarnessManifest> {
+        let name = name.filter(|n| !n.is_empty()).unwrap_or(DEFAULT_HARNESS);
+        self.harnesses.get(name)
+    }
+
+    /// Registered harness names, sorted.
+    pub fn names(&self) -> Vec<String> {
+        self.harnesses.keys().cloned().collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pi_manifest_renders_optional_groups() {
+        let pi = HarnessManifest::pi("/usr/local/bin/pi");
+        pi.validate().unwrap();
+        let launch = HarnessLaunch {
+            session_id: "ses_1".to_string(),
+            cwd: "/home/dev".to_string(),
+            model: Some("claude".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(
+            pi.render_args(&launch),
+            vec!["--mode", "rpc", "--approve", "--model", "claude"]
+        );
+    }
+
+    #[test]
+    fn test_manifest_parses_and_validates() {
+        let manifest: HarnessManifest = serde_json::from_value(serde_json::json!({
+            "name": "wrapped",
+            "binary": "wrapped-agent",
+            "args": ["--cwd={cwd}", ["--resume", "{session_file}"]],
+        }))
+        .unwrap();
+        assert_eq!(manifest.adapter, HarnessAdapter::Pi);
+        assert!(manifest.steering);
+        let launch = HarnessLaunch {
+            cwd: "/w".to_string(),
+            session_file: Some("/w/s.jsonl".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(
+            manifest.render_args(&launch),
+            vec!["--cwd=/w", "--resume", "/w/s.jsonl"]
+        );
+
+        let mut registry = HarnessRegistry::with_pi("pi");
+        assert!(registry.register(manifest).unwrap().is_none());
+        assert_eq!(registry.names(), vec!["pi", "wrapped"]);
+        assert_eq!(registry.get(None).unwrap().name, "pi");
+
+        let bad = HarnessManifest {
+            args: vec![ArgTemplate::Arg("{nope}".to_string())],
+            ..HarnessManifest::pi("pi")
+        };
+        assert!(matches!(
+            bad.validate(),
+            Err(HarnessError::UnknownPlaceholder { .. })
+        ));
+    }
+}
@@ -28,8 +28,10 @@ pub mod canon;
 pub mod commands;
 pub mod delegation;
 pub mod events;
+pub mod harness;
 pub mod messages;
 pub mod projection;
+pub mod replay;
 pub mod runner;
 pub mod timeline;
 
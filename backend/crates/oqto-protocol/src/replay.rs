@@ -0,0 +1,120 @@
+//! Sequence-numbered event log for replay after reconnects.
+//!
+//! Events are ephemeral, so a client that loses its connection mid-turn
+//! would otherwise miss every delta until the next full resync. The backend
+//! keeps the recent events of a session in an [`EventLog`]; each one gets a
+//! monotonically increasing `seq` (starting at 1) stamped into
+//! [`Event::seq`]. A client that reconnects passes the last `seq` it saw and
+//! receives what it missed. The log is bounded: when the oldest missed
+//! events are gone, [`Replay::missed`] tells the client to refetch state.
+
+use std::collections::VecDeque;
+
+use crate::events::Event;
+
+/// Events after a cursor.
+#[derive(Debug, Clone)]
+pub struct Replay {
+    /// Missed events, oldest first, with `seq` set.
+    pub events: Vec<Event>,
+    /// `seq` of the last returned event, or the cursor to continue from.
+    pub next_cursor: u64,
+    /// Events between the cursor and the first returned event were evicted.
+    pub missed: bool,
+}
+
+/// Bounded log of sequenced events.
+#[derive(Debug)]
+pub struct EventLog {
+    events: VecDeque<Event>,
+    next_seq: u64,
+    capacity: usize,
+}
+
+impl EventLog {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            events: VecDeque::new(),
+            next_seq: 1,
+            capacity: capacity.max(1),
+        }
+    }
+
+    /// Append an event, stamping its `seq`. Returns the stamped event.
+    pub fn push(&mut self, mut event: Event) -> &Event {
+        event.seq = Some(self.next_seq);
+        self.next_seq += 1;
+        self.events.push_back(event);
+        while self.events.len() > self.capacity {
+            self.events.pop_front();
+        }
+        self.events.back().expect("event was just pushed")
+    }
+
+    /// `seq` of the newest event (0 before the first).
+    pub fn last_seq(&self) -> u64 {
+        self.next_seq - 1
+    }
+
+    /// Events after `cursor` (everything retained when None), at most `max`.
+    pub fn since(&self, cursor: Option<u64>, max: usize) -> Replay {
+        let seq_of = |event: &Event| event.seq.unwrap_or_default();
+        let oldest = self.events.front().map_or(self.next_seq, seq_of);
+        let missed = cursor.is_some_and(|c| c.saturating_add(1) < oldest);
+        let after = cursor.unwrap_or(0);
+        let events: Vec<Event> = self
+            .events
+            .iter()
+            .filter(|event| seq_of(event) > after)
+            .take(max.max(1))
+            .cloned()
+            .collect();
+        let next_cursor = events
+            .last()
+            .map_or_else(|| after.max(oldest.saturating_sub(1)), seq_of);
+        Replay {
+            events,
+            next_cursor,
+            missed,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::events::EventPayload;
+
+    fn event(n: i64) -> Event {
+        Event {
+            session_id: "ses_1".to_string(),
+            runner_id: "local".to_string(),
+            ts: n,
+            seq: None,
+            payload: EventPayload::AgentIdle {
+                message_version: None,
+            },
+        }
+    }
+
+    #[test]
+    fn test_push_stamps_sequence_numbers() {
+        let mut log = EventLog::new(2);
+        assert_eq!(log.last_seq(), 0);
+        assert_eq!(log.push(event(0)).seq, Some(1));
+        assert_eq!(log.push(event(1)).seq, Some(2));
+        assert_eq!(log.push(event(2)).seq, Some(3));
+        assert_eq!(log.last_seq(), 3);
+
+        let replay = log.since(Some(1), 100);
+        assert!(!replay.missed);
+        assert_eq!(
+            replay.events.iter().map(|e| e.seq).collect::<Vec<_>>(),
+            vec![Some(2), Some(3)]
+        );
+        assert!(log.since(Some(0), 100).missed);
+
+        let json = serde_json::to_value(&replay.events[0]).unwrap();
+        assert_eq!(json["seq"], 2);
+    }
+}
@@ -0,0 +1,36 @@
+/**
+ * oqto tool approvals.
+ *
+ * Installed by the oqto runner when `[runner.tool_approvals]` is enabled.
+ * Before every tool call it asks the runner, which answers at once unless
+ * the call matches an approval rule; then the answer waits for the user.
+ * Anything but an explicit approval blocks the call.
+ */
+import type { ExtensionAPI } from "@mariozechner/pi-coding-agent";
+
+type Decision = { approved?: boolean; reason?: string | null };
+
+export default function (pi: ExtensionAPI) {
+	pi.on("tool_call", async (event, ctx) => {
+		const request = JSON.stringify({
+			toolCallId: event.toolCallId,
+			toolName: event.toolName,
+			input: event.input,
+		});
+		const answer = await ctx.ui.input("oqto_approval", request);
+
+		let decision: Decision = {};
+		try {
+			decision = JSON.parse(answer ?? "{}");
+		} catch {
+			// Unparseable answers deny the call.
+		}
+		if (decision.approved) return undefined;
+		return {
+			block: true,
+			reason: decision.reason
+				? `The user denied this tool call: ${decision.reason}`
+				: "The user denied this tool call.",
+		};
+	});
+}
@@ -0,0 +1,717 @@
+//! Files written by agents as first-class session artifacts.
+//!
+//! Every Pi session gets an artifacts directory in its workspace
+//! (`<cwd>/.oqto/artifacts/<session>/`, exported as `OQTO_ARTIFACTS_DIR`).
+//! Anything the agent writes there is a deliverable: after each tool call
+//! the runner scans it for new or changed files (matplotlib PNGs, reports,
+//! archives), copies them into the runner's artifact store
+//! (`<state>/oqto/artifacts/<id>/`), images with a PNG thumbnail, and emits
+//! an `artifact.created` event whose part points at the backend URL for the
+//! stored copy. When the agent goes idle, workspace files it wrote that match
+//! the configured globs (`[runner.artifacts] globs`, e.g. `dist/**`) are
+//! collected the same way. The copy is immutable and kept until it expires
+//! or is pruned, so the URL stays valid when the agent overwrites or deletes
+//! the original and after the session is gone.
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+use anyhow::{Context, Result};
+use log::{debug, info, warn};
+use serde::{Deserialize, Serialize};
+
+use oqto_protocol::Part;
+use oqto_protocol::canon::MediaDimensions;
+use oqto_protocol::events::{MediaArtifact, artifact_url};
+
+use crate::crash_bundle::write_private;
+
+/// Environment variable pointing the agent at its artifacts directory.
+pub const ARTIFACTS_DIR_ENV: &str = "OQTO_ARTIFACTS_DIR";
+
+/// Artifacts kept per runner; older ones are pruned after each capture.
+pub const DEFAULT_KEEP_ARTIFACTS: usize = 500;
+
+/// Default for how long artifacts are kept.
+pub const DEFAULT_RETENTION_DAYS: u64 = 30;
+
+/// Default for the largest file that is captured.
+const DEFAULT_MAX_FILE_BYTES: u64 = 20 * 1024 * 1024;
+
+/// Most workspace entries one glob collection looks at.
+const MAX_WORKSPACE_ENTRIES: usize = 20_000;
+
+/// Longest thumbnail edge in pixels.
+const THUMBNAIL_SIZE: u32 = 256;
+
+/// Files modified more recently than this may still be being written; they
+/// are picked up by the next scan.
+const SETTLE_TIME: Duration = Duration::from_millis(250);
+
+/// Artifact collection (`[runner.artifacts]`).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ArtifactConfig {
+    /// Workspace files collected when the agent goes idle, as globs relative
+    /// to the session's working directory (`dist/**`, `*.pdf`). `*` also
+    /// matches across directories.
+    pub globs: Vec<String>,
+    /// Artifacts kept per runner; the oldest are pruned first.
+    pub keep: usize,
+    /// Days an artifact is kept; 0 keeps it until `keep` prunes it.
+    pub retention_days: u64,
+    /// Larger files are left in the workspace.
+    pub max_file_bytes: u64,
+}
+
+impl Default for ArtifactConfig {
+    fn default() -> Self {
+        Self {
+            globs: Vec::new(),
+            keep: DEFAULT_KEEP_ARTIFACTS,
+            retention_days: DEFAULT_RETENTION_DAYS,
+            max_file_bytes: DEFAULT_MAX_FILE_BYTES,
+        }
+    }
+}
+
+/// Artifacts directory for a session in its workspace.
+pub fn session_artifacts_dir(cwd: &Path, session_id: &str) -> PathBuf {
+    let name: String = session_id
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '-' || c == '_' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    cwd.join(".oqto").join("artifacts").join(name)
+}
+
+/// MIME type for supported image extensions.
+fn image_mime(path: &Path) -> Option<&'static str> {
+    let ext = path.extension()?.to_str()?.to_ascii_lowercase();
+    Some(match ext.as_str() {
+        "png" => "image/png",
+        "jpg" | "jpeg" => "image/jpeg",
+        "gif" => "image/gif",
+        "webp" => "image/webp",
+        "svg" => "image/svg+xml",
+        _ => return None,
+    })
+}
+
+/// MIME type of any file, guessed from its extension.
+fn artifact_mime(path: &Path) -> &'static str {
+    image_mime(path)
+        .or_else(|| mime_guess::from_path(path).first_raw())
+        .unwrap_or("application/octet-stream")
+}
+
+/// Metadata stored next to a captured file.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct StoredArtifact {
+    session_id: String,
+    /// File name of the captured copy inside the artifact directory.
+    file: String,
+    artifact: MediaArtifact,
+}
+
+impl StoredArtifact {
+    fn expired(&self, now: i64) -> bool {
+        self.artifact.expires_at.is_some_and(|at| at <= now)
+    }
+}
+
+/// Captured artifact content.
+#[derive(Debug, Clone)]
+pub struct ArtifactContent {
+    pub session_id: String,
+    pub artifact: MediaArtifact,
+    pub mime_type: String,
+    pub data: Vec<u8>,
+}
+
+/// On-disk artifact store.
+#[derive(Debug, Clone)]
+pub struct ArtifactStore {
+    dir: PathBuf,
+    keep: usize,
+    retention: Option<Duration>,
+    max_file_bytes: u64,
+}
+
+impl ArtifactStore {
+    pub fn new(dir: PathBuf, config: &ArtifactConfig) -> Self {
+        Self {
+            dir,
+            keep: config.keep.max(1),
+            retention: (config.retention_days > 0)
+                .then(|| Duration::from_secs(config.retention_days * 24 * 60 * 60)),
+            max_file_bytes: config.max_file_bytes,
+        }
+    }
+
+    /// Copy `source` into the store. `path` is its workspace-relative path
+    /// when it was matched by a glob. Returns None for empty or oversized
+    /// files.
+    pub async fn capture(
+        &self,
+        session_id: &str,
+        source: &Path,
+        path: Option<&str>,
+        tool_call_id: Option<&str>,
+    ) -> Result<Option<MediaArtifact>> {
+        let mime_type = artifact_mime(source);
+        let filename = source
+            .file_name()
+            .map(|n| n.to_string_lossy().into_owned())
+            .unwrap_or_else(|| "artifact".to_string());
+        let size = tokio::fs::metadata(source)
+            .await
+            .with_context(|| format!("stat {}", source.display()))?
+            .len();
+        if size == 0 || size > self.max_file_bytes {
+            debug!("Skipping artifact {} ({} bytes)", source.display(), size);
+            return Ok(None);
+        }
+        let data = tokio::fs::read(source)
+            .await
+            .with_context(|| format!("reading {}", source.display()))?;
+
+        let id = format!("art_{}", uuid::Uuid::new_v4().simple());
+        let artifact_dir = self.dir.join(&id);
+        tokio::fs::create_dir_all(&artifact_dir)
+            .await
+            .with_context(|| format!("creating {}", artifact_dir.display()))?;
+        let ext = source
+            .extension()
+            .map(|e| e.to_string_lossy().to_ascii_lowercase())
+            .unwrap_or_default();
+        let file = if ext.is_empty() {
+            "original".to_string()
+        } else {
+            format!("original.{ext}")
+        };
+        write_private(&artifact_dir.join(&file), &data).await?;
+
+        // Raster images get a PNG thumbnail; SVGs scale on their own.
+        let is_image = image_mime(source).is_some();
+        let (dimensions, has_thumbnail) = if !is_image || mime_type == "image/svg+xml" {
+            (None, false)
+        } else {
+            match tokio::task::spawn_blocking(move || make_thumbnail(&data)).await {
+                Ok(Ok((dims, thumb))) => {
+                    write_private(&artifact_dir.join("thumbnail.png"), &thumb).await?;
+                    (Some(dims), true)
+                }
+                Ok(Err(e)) => {
+                    debug!("No thumbnail for {}: {:#}", source.display(), e);
+                    (None, false)
+                }
+                Err(e) => {
+                    warn!("Thumbnail task failed for {}: {}", source.display(), e);
+                    (None, false)
+                }
+            }
+        };
+
+        let url = artifact_url(session_id, &id, false);
+        let part_id = format!("part_{}", uuid::Uuid::new_v4().simple());
+        let source_url = oqto_protocol::MediaSource::url(url.as_str());
+        let part = if is_image {
+            Part::Image {
+                id: part_id,
+                source: source_url,
+                alt: Some(filename.clone()),
+            }
+        } else {
+            Part::Attachment {
+                id: part_id,
+                source: source_url,
+                filename: Some(filename.clone()),
+                size_bytes: Some(size),
+            }
+        };
+        let created_at = chrono::Utc::now().timestamp_millis();
+        let artifact = MediaArtifact {
+            part,
+            id: id.clone(),
+            filename,
+            mime_type: mime_type.to_string(),
+            size_bytes: size,
+            dimensions,
+            thumbnail_url: has_thumbnail.then(|| artifact_url(session_id, &id, true)),
+            url,
+            tool_call_id: tool_call_id.map(str::to_string),
+            path: path.map(str::to_string),
+            created_at,
+            expires_at: self
+                .retention
+                .map(|retention| created_at + retention.as_millis() as i64),
+        };
+        let stored = StoredArtifact {
+            session_id: session_id.to_string(),
+            file,
+            artifact: artifact.clone(),
+        };
+        write_private(
+            &artifact_dir.join("artifact.json"),
+            &serde_json::to_vec(&stored).context("serializing artifact")?,
+        )
+        .await?;
+
+        info!(
+            "Captured artifact {} ({}) for session '{}'",
+            id,
+            source.display(),
+            session_id
+        );
+        if let Err(e) = self.prune().await {
+            warn!("Failed to prune artifacts: {:#}", e);
+        }
+        Ok(Some(artifact))
+    }
+
+    /// Unexpired artifacts of a session, oldest first.
+    pub async fn list(&self, session_id: &str) -> Result<Vec<MediaArtifact>> {
+        let now = chrono::Utc::now().timestamp_millis();
+        Ok(self
+            .load_all()
+            .await?
+            .into_iter()
+            .filter(|stored| stored.session_id == session_id && !stored.expired(now))
+            .map(|stored| stored.artifact)
+            .collect())
+    }
+
+    /// Load an artifact's file (or its thumbnail).
+    pub async fn get(&self, artifact_id: &str, thumbnail: bool) -> Result<Option<ArtifactContent>> {
+        if !is_valid_artifact_id(artifact_id) {
+            anyhow::bail!("invalid artifact id: {artifact_id}");
+        }
+        let artifact_dir = self.dir.join(artifact_id);
+        let Some(stored) = read_stored(&artifact_dir.join("artifact.json")).await? else {
+            return Ok(None);
+        };
+        if stored.expired(chrono::Utc::now().timestamp_millis()) {
+            return Ok(None);
+        }
+        let (path, mime_type) = if thumbnail && stored.artifact.thumbnail_url.is_some() {
+            (artifact_dir.join("thumbnail.png"), "image/png".to_string())
+        } else {
+            (
+                artifact_dir.join(&stored.file),
+                stored.artifact.mime_type.clone(),
+            )
+        };
+        let data = tokio::fs::read(&path)
+            .await
+            .with_context(|| format!("reading {}", path.display()))?;
+        Ok(Some(ArtifactContent {
+            session_id: stored.session_id,
+            artifact: stored.artifact,
+            mime_type,
+            data,
+        }))
+    }
+
+    /// Every stored artifact, oldest first.
+    async fn load_all(&self) -> Result<Vec<StoredArtifact>> {
+        let mut artifacts = Vec::new();
+        let mut entries = match tokio::fs::read_dir(&self.dir).await {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(artifacts),
+            Err(e) => return Err(e).with_context(|| format!("reading {}", self.dir.display())),
+        };
+        while let Some(entry) = entries.next_entry().await? {
+            match read_stored(&entry.path().join("artifact.json")).await {
+                Ok(Some(stored)) => artifacts.push(stored),
+                Ok(None) => {}
+                Err(e) => debug!("Skipping unreadable artifact: {:#}", e),
+            }
+        }
+        artifacts.sort_by_key(|stored| stored.artifact.created_at);
+        Ok(artifacts)
+    }
+
+    /// Delete expired artifacts and the oldest beyond `keep`.
+    async fn prune(&self) -> Result<()> {
+        let now = chrono::Utc::now().timestamp_millis();
+        let (expired, live): (Vec<_>, Vec<_>) = self
+            .load_all()
+            .await?
+            .into_iter()
+            .partition(|stored| stored.expired(now));
+        let excess = live.len().saturating_sub(self.keep);
+        for stale in expired.iter().chain(live.iter().take(excess)) {
+            let dir = self.dir.join(&stale.artifact.id);
+            tokio::fs::remove_dir_all(&dir)
+                .await
+                .with_context(|| format!("removing {}", dir.display()))?;
+        }
+        Ok(())
+    }
+}
+
+fn is_valid_artifact_id(id: &str) -> bool {
+    id.starts_with("art_") && id.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+async fn read_stored(path: &Path) -> Result<Option<StoredArtifact>> {
+    let raw = match tokio::fs::read(path).await {
+        Ok(raw) => raw,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(e).with_context(|| format!("reading {}", path.display())),
+    };
+    serde_json::from_slice(&raw)
+        .map(Some)
+        .with_context(|| format!("parsing {}", path.display()))
+}
+
+/// Decode an image and render a PNG thumbnail.
+fn make_thumbnail(data: &[u8]) -> Result<(MediaDimensions, Vec<u8>)> {
+    let image = image::load_from_memory(data).context("decoding image")?;
+    let dimensions = MediaDimensions::new(image.width(), image.height());
+    let thumb = image.thumbnail(THUMBNAIL_SIZE, THUMBNAIL_SIZE);
+    let mut out = std::io::Cursor::new(Vec::new());
+    thumb
+        .write_to(&mut out, image::ImageFormat::Png)
+        .context("encoding thumbnail")?;
+    Ok((dimensions, out.into_inner()))
+}
+
+/// A file to capture: its path, workspace-relative path when matched by a
+/// glob, and mtime and size.
+type Candidate = (PathBuf, Option<String>, (SystemTime, u64));
+
+/// Watches one session's artifacts directory and, with globs, its workspace.
+pub struct ArtifactWatch {
+    store: std::sync::Arc<ArtifactStore>,
+    dir: PathBuf,
+    session_id: String,
+    /// Working directory the globs are relative to.
+    workspace: PathBuf,
+    globs: Vec<glob::Pattern>,
+    /// Workspace files older than this were not written by the session.
+    started: SystemTime,
+    /// Files already captured, with the mtime and size they had.
+    seen: tokio::sync::Mutex<HashMap<PathBuf, (SystemTime, u64)>>,
+}
+
+impl ArtifactWatch {
+    /// Create the directory. Files already in it (from an earlier run of a
+    /// resumed session) are not captured again.
+    pub fn new(store: std::sync::Arc<ArtifactStore>, dir: PathBuf, session_id: String) -> Self {
+        if let Err(e) = std::fs::create_dir_all(&dir) {
+            warn!("Failed to create artifacts dir {}: {}", dir.display(), e);
+        }
+        let seen = std::fs::read_dir(&dir)
+            .map(|entries| {
+                entries
+                    .flatten()
+                    .filter_map(|entry| {
+                        let meta = entry.metadata().ok()?;
+                        Some((entry.path(), (meta.modified().ok()?, meta.len())))
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+        Self {
+            store,
+            dir,
+            session_id,
+            workspace: PathBuf::new(),
+            globs: Vec::new(),
+            started: SystemTime::now(),
+            seen: tokio::sync::Mutex::new(seen),
+        }
+    }
+
+    /// Also collect files under `workspace` matching `globs`. Invalid
+    /// patterns are skipped.
+    pub fn with_globs(mut self, workspace: PathBuf, globs: &[String]) -> Self {
+        self.globs = globs
+            .iter()
+            .filter_map(|glob| match glob::Pattern::new(glob) {
+                Ok(pattern) => Some(pattern),
+                Err(e) => {
+                    warn!("Ignoring invalid artifact glob '{}': {}", glob, e);
+                    None
+                }
+            })
+            .collect();
+        self.workspace = workspace;
+        self
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Capture new or changed files in the artifacts directory.
+    pub async fn scan(&self, tool_call_id: Option<&str>) -> Vec<MediaArtifact> {
+        let mut candidates = Vec::new();
+        if let Ok(mut entries) = tokio::fs::read_dir(&self.dir).await {
+            while let Ok(Some(entry)) = entries.next_entry().await {
+                let Ok(meta) = entry.metadata().await else {
+                    continue;
+                };
+                if let (true, Ok(modified)) = (meta.is_file(), meta.modified()) {
+                    candidates.push((entry.path(), None, (modified, meta.len())));
+                }
+            }
+        }
+        self.capture_new(candidates, tool_call_id).await
+    }
+
+    /// Capture workspace files matching the globs that the session wrote
+    /// or changed.
+    pub async fn collect(&self) -> Vec<MediaArtifact> {
+        if self.globs.is_empty() {
+            return Vec::new();
+        }
+        let workspace = self.workspace.clone();
+        let globs = self.globs.clone();
+        let started = self.started;
+        let candidates = tokio::task::spawn_blocking(move || {
+            matching_files(&workspace, &globs)
+                .into_iter()
+                .filter(|(_, _, (modified, _))| *modified >= started)
+                .collect()
+        })
+        .await
+        .unwrap_or_default();
+        self.capture_new(candidates, None).await
+    }
+
+    async fn capture_new(
+        &self,
+        candidates: Vec<Candidate>,
+        tool_call_id: Option<&str>,
+    ) -> Vec<MediaArtifact> {
+        let mut seen = self.seen.lock().await;
+        let mut captured = Vec::new();
+        let now = SystemTime::now();
+        for (path, relative, stamp) in candidates {
+            if seen.get(&path) == Some(&stamp) {
+                continue;
+            }
+            if now.duration_since(stamp.0).unwrap_or_default() < SETTLE_TIME {
+                continue;
+            }
+            match self
+                .store
+                .capture(&self.session_id, &path, relative.as_deref(), tool_call_id)
+                .await
+            {
+                Ok(Some(artifact)) => captured.push(artifact),
+                Ok(None) => {}
+                Err(e) => warn!("Failed to capture artifact {}: {:#}", path.display(), e),
+            }
+            seen.insert(path, stamp);
+        }
+        captured
+    }
+}
+
+/// Files under `workspace` matching any of `globs`, with their
+/// workspace-relative path, mtime and size. Hidden directories (including
+/// `.oqto` and `.git`) and `node_modules` are skipped.
+fn matching_files(workspace: &Path, globs: &[glob::Pattern]) -> Vec<Candidate> {
+    let mut matches = Vec::new();
+    let mut pending = vec![workspace.to_path_buf()];
+    let mut visited = 0;
+    while let Some(dir) = pending.pop() {
+        let Ok(entries) = std::fs::read_dir(&dir) else {
+            continue;
+        };
+        for entry in entries.flatten() {
+            visited += 1;
+            if visited > MAX_WORKSPACE_ENTRIES {
+                debug!(
+                    "Artifact globs stopped after {} entries in {}",
+                    MAX_WORKSPACE_ENTRIES,
+                    workspace.display()
+                );
+                return matches;
+            }
+            let Ok(file_type) = entry.file_type() else {
+                continue;
+            };
+            let path = entry.path();
+            if file_type.is_dir() {
+                let name = entry.file_name();
+                let name = name.to_string_lossy();
+                if !name.starts_with('.') && name != "node_modules" {
+                    pending.push(path);
+                }
+                continue;
+            }
+            if !file_type.is_file() {
+                continue;
+            }
+            let Ok(relative) = path.strip_prefix(workspace) else {
+                continue;
+            };
+            let relative = relative.to_string_lossy().replace('\\', "/");
+            if !globs.iter().any(|glob| glob.matches(&relative)) {
+                continue;
+            }
+            let Ok(meta) = entry.metadata() else {
+                continue;
+            };
+            if let Ok(modified) = meta.modified() {
+                matches.push((path, Some(relative), (modified, meta.len())));
+            }
+        }
+    }
+    matches
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+
+    fn png(width: u32, height: u32) -> Vec<u8> {
+        let image = image::RgbImage::new(width, height);
+        let mut out = std::io::Cursor::new(Vec::new());
+        image.write_to(&mut out, image::ImageFormat::Png).unwrap();
+        out.into_inner()
+    }
+
+    fn backdate(path: &Path) {
+        let file = std::fs::File::options().write(true).open(path).unwrap();
+        file.set_modified(SystemTime::now() - Duration::from_secs(5))
+            .unwrap();
+    }
+
+    #[test]
+    fn session_dir_is_sanitized() {
+        assert_eq!(
+            session_artifacts_dir(Path::new("/work"), "../ses 1"),
+            PathBuf::from("/work/.oqto/artifacts/___ses_1")
+        );
+    }
+
+    #[tokio::test]
+    async fn scan_captures_new_and_changed_images() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = ArtifactConfig {
+            keep: 10,
+            ..Default::default()
+        };
+        let store = Arc::new(ArtifactStore::new(dir.path().join("store"), &config));
+        let artifacts_dir = dir.path().join("artifacts");
+        std::fs::create_dir_all(&artifacts_dir).unwrap();
+        let old = artifacts_dir.join("old.png");
+        std::fs::write(&old, png(4, 4)).unwrap();
+        backdate(&old);
+
+        let watch = ArtifactWatch::new(store.clone(), artifacts_dir.clone(), "ses_a".to_string());
+        let plot = artifacts_dir.join("plot.png");
+        std::fs::write(&plot, png(600, 300)).unwrap();
+        backdate(&plot);
+
+        let captured = watch.scan(Some("call_1")).await;
+        assert_eq!(captured.len(), 1);
+        let artifact = &captured[0];
+        assert!(matches!(artifact.part, Part::Image { .. }));
+        assert_eq!(artifact.filename, "plot.png");
+        assert_eq!(artifact.mime_type, "image/png");
+        assert_eq!(artifact.tool_call_id.as_deref(), Some("call_1"));
+        assert_eq!(
+            artifact.url,
+            format!("/api/sessions/ses_a/artifacts/{}", artifact.id)
+        );
+        let dims = artifact.dimensions.as_ref().unwrap();
+        assert_eq!((dims.width, dims.height), (600, 300));
+
+        // Unchanged files are not captured twice.
+        assert!(watch.scan(None).await.is_empty());
+
+        let thumb = store.get(&artifact.id, true).await.unwrap().unwrap();
+        assert_eq!(thumb.mime_type, "image/png");
+        let decoded = image::load_from_memory(&thumb.data).unwrap();
+        assert_eq!((decoded.width(), decoded.height()), (256, 128));
+        let original = store.get(&artifact.id, false).await.unwrap().unwrap();
+        assert_eq!(original.data, std::fs::read(&plot).unwrap());
+
+        // Overwriting the file captures a new artifact; the first is kept.
+        std::fs::write(&plot, png(10, 10)).unwrap();
+        backdate(&plot);
+        let again = watch.scan(None).await;
+        assert_eq!(again.len(), 1);
+        assert_ne!(again[0].id, artifact.id);
+        assert_eq!(store.list("ses_a").await.unwrap().len(), 2);
+        assert!(store.list("ses_b").await.unwrap().is_empty());
+
+        assert!(store.get("../etc", false).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn deliverables_and_glob_matches_are_collected() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = Arc::new(ArtifactStore::new(
+            dir.path().join("store"),
+            &ArtifactConfig::default(),
+        ));
+        let workspace = dir.path().join("work");
+        std::fs::create_dir_all(workspace.join("dist/assets")).unwrap();
+        std::fs::create_dir_all(workspace.join("node_modules/pkg")).unwrap();
+        std::fs::write(workspace.join("stale.pdf"), "%PDF old").unwrap();
+        backdate(&workspace.join("stale.pdf"));
+        let artifacts_dir = session_artifacts_dir(&workspace, "ses_a");
+
+        let watch = ArtifactWatch::new(store.clone(), artifacts_dir.clone(), "ses_a".to_string())
+            .with_globs(
+                workspace.clone(),
+                &["dist/**".to_string(), "*.pdf".to_string(), "[".to_string()],
+            );
+        let files = [
+            artifacts_dir.join("report.csv"),
+            workspace.join("dist/assets/app.js"),
+            workspace.join("docs.pdf"),
+            workspace.join("node_modules/pkg/manual.pdf"),
+            workspace.join("src.rs"),
+        ];
+        for file in &files {
+            std::fs::create_dir_all(file.parent().unwrap()).unwrap();
+            std::fs::write(file, "content").unwrap();
+        }
+        tokio::time::sleep(SETTLE_TIME * 2).await;
+
+        let delivered = watch.scan(Some("call_1")).await;
+        assert_eq!(delivered.len(), 1);
+        assert_eq!(delivered[0].filename, "report.csv");
+        assert_eq!(delivered[0].mime_type, "text/csv");
+        assert_eq!(delivered[0].path, None);
+        assert!(matches!(
+            delivered[0].part,
+            Part::Attachment {
+                size_bytes: Some(7),
+                ..
+            }
+        ));
+        let expires_at = delivered[0].expires_at.unwrap();
+        assert_eq!(
+            expires_at - delivered[0].created_at,
+            DEFAULT_RETENTION_DAYS as i64 * 24 * 60 * 60 * 1000
+        );
+
+        let mut collected: Vec<String> = watch
+            .collect()
+            .await
+            .into_iter()
+            .filter_map(|artifact| artifact.path)
+            .collect();
+        collected.sort();
+        assert_eq!(collected, vec!["dist/assets/app.js", "docs.pdf"]);
+        assert!(watch.collect().await.is_empty());
+        assert_eq!(store.list("ses_a").await.unwrap().len(), 3);
+    }
+}
@@ -0,0 +1,192 @@
+//! Supervised background processes started by agents.
+//!
+//! Agents start long-running processes (dev servers, file watchers) with
+//! `oqtoctl bg start`. The runner spawns each one as a managed RPC process
+//! (sandboxed when a sandbox config is loaded) so its output is buffered,
+//! checks on it every few seconds, restarts it after a crash when asked to,
+//! and stops it when its session closes. Without this, such processes die
+//! with the agent's bash call or linger untracked after the session ends.
+
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+use crate::protocol::{BackgroundProcessInfo, BackgroundProcessStatus, RestartPolicy};
+
+/// Most live background processes one session may have.
+pub const MAX_PER_SESSION: usize = 8;
+
+/// Restarts after crashes before the runner gives up.
+pub const MAX_RESTARTS: u32 = 5;
+
+/// How often the runner checks background processes for exits.
+pub const SUPERVISE_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Lines returned by a logs request without an explicit count.
+pub const DEFAULT_LOG_LINES: usize = 200;
+
+/// Output of earlier runs kept across restarts.
+const PREVIOUS_RUN_LINES: usize = 500;
+
+/// A background process and its supervision state.
+#[derive(Debug, Clone)]
+pub struct BackgroundProcess {
+    pub info: BackgroundProcessInfo,
+    pub env: HashMap<String, String>,
+    /// Set while waiting to restart after a crash.
+    pub restart_at: Option<Instant>,
+    /// Tail of the output of earlier runs, oldest first.
+    pub previous_output: Vec<String>,
+}
+
+impl BackgroundProcess {
+    pub fn is_live(&self) -> bool {
+        matches!(
+            self.info.status,
+            BackgroundProcessStatus::Running | BackgroundProcessStatus::Restarting
+        )
+    }
+
+    /// Record that the current run exited. Returns true when a restart was
+    /// scheduled.
+    pub fn record_exit(&mut self, exit_code: Option<i32>, output: &[String], now: Instant) -> bool {
+        self.info.exit_code = exit_code;
+        self.info.ended_at = Some(chrono::Utc::now().to_rfc3339());
+        self.previous_output.extend(output.iter().cloned());
+        let excess = self
+            .previous_output
+            .len()
+            .saturating_sub(PREVIOUS_RUN_LINES);
+        self.previous_output.drain(..excess);
+
+        if should_restart(self.info.restart, exit_code, self.info.restarts) {
+            self.info.status = BackgroundProcessStatus::Restarting;
+            self.restart_at = Some(now + restart_delay(self.info.restarts));
+            true
+        } else {
+            self.info.status = BackgroundProcessStatus::Exited;
+            self.restart_at = None;
+            false
+        }
+    }
+
+    /// The restart backoff has elapsed.
+    pub fn restart_due(&self, now: Instant) -> bool {
+        self.info.status == BackgroundProcessStatus::Restarting
+            && self.restart_at.is_some_and(|at| at <= now)
+    }
+
+    /// Last `lines` of output across runs. `current` is the running (or
+    /// last) run's output.
+    pub fn tail(&self, current: &[String], lines: usize) -> Vec<String> {
+        let mut all: Vec<String> = self.previous_output.clone();
+        if !self.previous_output.is_empty() && !current.is_empty() {
+            all.push(format!(
+                "--- restarted ({} of {MAX_RESTARTS}) ---",
+                self.info.restarts
+            ));
+        }
+        all.extend(current.iter().cloned());
+        let skip = all.len().saturating_sub(lines);
+        all.split_off(skip)
+    }
+}
+
+/// Whether a run that exited with `exit_code` should be restarted.
+/// Killed-by-signal runs have no exit code and count as crashes.
+pub fn should_restart(policy: RestartPolicy, exit_code: Option<i32>, restarts: u32) -> bool {
+    policy == RestartPolicy::OnFailure && exit_code != Some(0) && restarts < MAX_RESTARTS
+}
+
+/// Backoff before restart number `restarts + 1`: 1s, 2s, 4s, ... up to 30s.
+pub fn restart_delay(restarts: u32) -> Duration {
+    Duration::from_secs(1u64 << restarts.min(5)).min(Duration::from_secs(30))
+}
+
+/// Program and arguments that run `command` with stderr folded into stdout,
+/// so both end up in the process's output buffer.
+pub fn spawn_command(command: &[String]) -> (String, Vec<String>) {
+    let mut args = vec![
+        "-c".to_string(),
+        "exec \"$@\" 2>&1".to_string(),
+        "sh".to_string(),
+    ];
+    args.extend(command.iter().cloned());
+    ("sh".to_string(), args)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    fn process(restart: RestartPolicy) -> BackgroundProcess {
+        BackgroundProcess {
+            info: BackgroundProcessInfo {
+                id: "bg-1".to_string(),
+                session_id: "ses_1".to_string(),
+                name: "dev".to_string(),
+                command: vec!["npm".to_string(), "run".to_string(), "dev".to_string()],
+                cwd: PathBuf::from("/tmp"),
+                restart,
+                status: BackgroundProcessStatus::Running,
+                pid: Some(42),
+                exit_code: None,
+                restarts: 0,
+                started_at: chrono::Utc::now().to_rfc3339(),
+                ended_at: None,
+            },
+            env: HashMap::new(),
+            restart_at: None,
+            previous_output: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn restarts_crashes_with_backoff() {
+        let now = Instant::now();
+        let mut proc = process(RestartPolicy::OnFailure);
+
+        assert!(proc.record_exit(Some(1), &["boom".to_string()], now));
+        assert_eq!(proc.info.status, BackgroundProcessStatus::Restarting);
+        assert!(!proc.restart_due(now));
+        assert!(proc.restart_due(now + Duration::from_secs(1)));
+        assert!(proc.is_live());
+
+        // A clean exit is not restarted.
+        assert!(!proc.record_exit(Some(0), &[], now));
+        assert_eq!(proc.info.status, BackgroundProcessStatus::Exited);
+        assert!(!proc.is_live());
+    }
+
+    #[test]
+    fn gives_up_after_max_restarts() {
+        assert!(should_restart(RestartPolicy::OnFailure, None, 0));
+        assert!(!should_restart(
+            RestartPolicy::OnFailure,
+            Some(1),
+            MAX_RESTARTS
+        ));
+        assert!(!should_restart(RestartPolicy::Never, Some(1), 0));
+        assert_eq!(restart_delay(0), Duration::from_secs(1));
+        assert_eq!(restart_delay(3), Duration::from_secs(8));
+        assert_eq!(restart_delay(20), Duration::from_secs(30));
+    }
+
+    #[test]
+    fn tail_spans_restarts() {
+        let mut proc = process(RestartPolicy::OnFailure);
+        proc.record_exit(Some(1), &["a".to_string(), "b".to_string()], Instant::now());
+        proc.info.restarts = 1;
+
+        let tail = proc.tail(&["c".to_string()], 10);
+        assert_eq!(tail, vec!["a", "b", "--- restarted (1 of 5) ---", "c"]);
+        assert_eq!(proc.tail(&["c".to_string()], 2).len(), 2);
+    }
+
+    #[test]
+    fn folds_stderr_into_output() {
+        let (program, args) = spawn_command(&["npm".to_string(), "run".to_string()]);
+        assert_eq!(program, "sh");
+        assert_eq!(args, vec!["-c", "exec \"$@\" 2>&1", "sh", "npm", "run"]);
+    }
+}
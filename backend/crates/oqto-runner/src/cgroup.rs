@@ -0,0 +1,297 @@
+//! cgroup v2 resource caps for agent processes in local mode.
+//!
+//! The `[resources]` section of sandbox.toml caps memory, CPU time and
+//! process count. The runner needs a delegated cgroup (`Delegate=yes` on its
+//! systemd unit): it moves itself into a `runner` leaf so the controllers can
+//! be enabled, and puts agents under `agents/`, which carries the caps for
+//! all of the user's agents together. Each session gets a leaf of its own
+//! under it, capped the same way (tighter if its workspace says so), whose
+//! `memory.events` tell which session lost a process to the OOM killer.
+
+use std::fs::{self, File, OpenOptions};
+use std::os::fd::AsRawFd;
+use std::os::unix::process::CommandExt;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result, bail};
+use log::debug;
+use oqto_sandbox::ResourceConfig;
+
+/// Where the unified hierarchy is mounted.
+const CGROUP_MOUNT: &str = "/sys/fs/cgroup";
+
+/// Leaf the runner moves its own processes into.
+const RUNNER_LEAF: &str = "runner";
+
+/// Parent of the session leaves.
+const AGENTS_GROUP: &str = "agents";
+
+/// `cpu.max` period in microseconds.
+const CPU_PERIOD_US: u64 = 100_000;
+
+/// The runner's `agents` cgroup.
+#[derive(Debug)]
+pub struct AgentCgroups {
+    root: PathBuf,
+    resources: ResourceConfig,
+}
+
+impl AgentCgroups {
+    /// Set up the `agents` cgroup under the runner's own with `resources`.
+    pub fn setup(resources: &ResourceConfig) -> Result<Self> {
+        let proc_cgroup =
+            fs::read_to_string("/proc/self/cgroup").context("Failed to read /proc/self/cgroup")?;
+        let own = own_cgroup(&proc_cgroup)
+            .context("The runner is not in a cgroup v2 hierarchy (cgroup v1 is not supported)")?;
+        let mut base = Path::new(CGROUP_MOUNT).join(own.trim_start_matches('/'));
+        // Restarted inside the leaf it moved into before.
+        if base.file_name().is_some_and(|name| name == RUNNER_LEAF) {
+            base.pop();
+        }
+
+        let controllers = controllers(resources);
+        let available = fs::read_to_string(base.join("cgroup.controllers"))
+            .with_context(|| format!("Failed to read the controllers of {}", base.display()))?;
+        for controller in &controllers {
+            if !available.split_whitespace().any(|c| c == *controller) {
+                bail!(
+                    "cgroup controller '{}' is not delegated to {} (set Delegate=yes on the runner's unit)",
+                    controller,
+                    base.display()
+                );
+            }
+        }
+
+        // Controllers can only be enabled for children of a cgroup without
+        // processes of its own, so everything in it moves to the leaf.
+        let runner_leaf = base.join(RUNNER_LEAF);
+        create_dir(&runner_leaf)?;
+        let procs = fs::read_to_string(base.join("cgroup.procs")).unwrap_or_default();
+        for pid in procs.lines().filter(|line| !line.trim().is_empty()) {
+            fs::write(runner_leaf.join("cgroup.procs"), pid)
+                .with_context(|| format!("Failed to move process {pid} into {RUNNER_LEAF}/"))?;
+        }
+        enable_controllers(&base, &controllers)?;
+
+        let root = base.join(AGENTS_GROUP);
+        create_dir(&root)?;
+        write_limits(&root, resources)?;
+        enable_controllers(&root, &controllers)?;
+
+        Ok(Self {
+            root,
+            resources: resources.clone(),
+        })
+    }
+
+    /// Create the leaf of a session, capped with `resources` and no looser
+    /// than the agents' caps.
+    pub fn session(&self, session_id: &str, resources: &ResourceConfig) -> Result<SessionCgroup> {
+        let path = self.root.join(leaf_name(session_id));
+        create_dir(&path)?;
+        let resources = self.resources.tighter(resources);
+        write_limits(&path, &resources)?;
+        let procs = OpenOptions::new()
+            .write(true)
+            .open(path.join("cgroup.procs"))
+            .with_context(|| format!("Failed to open {}/cgroup.procs", path.display()))?;
+        // A leaf left over from an earlier run keeps its counts.
+        let oom_kills_before = read_oom_kills(&path);
+        Ok(SessionCgroup {
+            path,
+            procs,
+            resources,
+            oom_kills_before,
+        })
+    }
+}
+
+/// A session's leaf cgroup. Removed when dropped, once its processes are
+/// gone.
+#[derive(Debug)]
+pub struct SessionCgroup {
+    path: PathBuf,
+    procs: File,
+    resources: ResourceConfig,
+    oom_kills_before: u64,
+}
+
+impl SessionCgroup {
+    /// Caps of the session.
+    pub fn resources(&self) -> &ResourceConfig {
+        &self.resources
+    }
+
+    /// Start `cmd` in this cgroup. The child joins it before exec, so
+    /// nothing it starts escapes the caps.
+    pub fn attach(&self, cmd: &mut std::process::Command) {
+        let fd = self.procs.as_raw_fd();
+        // SAFETY: pre_exec runs in the child after fork; write(2) is
+        // async-signal-safe and the fd stays open until exec.
+        unsafe {
+            cmd.pre_exec(move || {
+                if libc::write(fd, b"0".as_ptr().cast(), 1) == -1 {
+                    return Err(std::io::Error::last_os_error());
+                }
+                Ok(())
+            });
+        }
+    }
+
+    /// Processes the OOM killer killed in this cgroup since it was set up
+    /// for the session.
+    pub fn oom_kills(&self) -> u64 {
+        read_oom_kills(&self.path).saturating_sub(self.oom_kills_before)
+    }
+}
+
+impl Drop for SessionCgroup {
+    fn drop(&mut self) {
+        // Fails while processes the agent started are still running; the
+        // leaf is reused if the session starts again.
+        if let Err(e) = fs::remove_dir(&self.path) {
+            debug!("Keeping cgroup {}: {}", self.path.display(), e);
+        }
+    }
+}
+
+/// Path of the cgroup v2 entry (`0::/path`) in `/proc/self/cgroup`.
+fn own_cgroup(proc_cgroup: &str) -> Option<&str> {
+    proc_cgroup
+        .lines()
+        .find_map(|line| line.strip_prefix("0::"))
+        .map(str::trim)
+}
+
+/// Controllers needed for `resources`.
+fn controllers(resources: &ResourceConfig) -> Vec<&'static str> {
+    let mut controllers = Vec::new();
+    if resources.memory_mb.is_some() {
+        controllers.push("memory");
+    }
+    if resources.cpus.is_some() {
+        controllers.push("cpu");
+    }
+    if resources.pids_limit.is_some() {
+        controllers.push("pids");
+    }
+    controllers
+}
+
+/// Directory name for a session's leaf.
+fn leaf_name(session_id: &str) -> String {
+    let name: String = session_id
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '-' || c == '_' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    format!("session-{name}")
+}
+
+/// `cpu.max` value for a number of cores.
+fn cpu_max(cpus: f64) -> String {
+    let quota = ((cpus * CPU_PERIOD_US as f64).round() as u64).max(1_000);
+    format!("{quota} {CPU_PERIOD_US}")
+}
+
+fn read_oom_kills(path: &Path) -> u64 {
+    fs::read_to_string(path.join("memory.events"))
+        .map(|events| parse_oom_kills(&events))
+        .unwrap_or(0)
+}
+
+/// `oom_kill` count of a `memory.events` file.
+fn parse_oom_kills(events: &str) -> u64 {
+    events
+        .lines()
+        .find_map(|line| line.strip_prefix("oom_kill "))
+        .and_then(|count| count.trim().parse().ok())
+        .unwrap_or(0)
+}
+
+fn create_dir(path: &Path) -> Result<()> {
+    match fs::create_dir(path) {
+        Ok(()) => Ok(()),
+        Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => Ok(()),
+        Err(e) => Err(e).with_context(|| format!("Failed to create cgroup {}", path.display())),
+    }
+}
+
+fn enable_controllers(path: &Path, controllers: &[&str]) -> Result<()> {
+    if controllers.is_empty() {
+        return Ok(());
+    }
+    let value = controllers
+        .iter()
+        .map(|c| format!("+{c}"))
+        .collect::<Vec<_>>()
+        .join(" ");
+    fs::write(path.join("cgroup.subtree_control"), value)
+        .with_context(|| format!("Failed to enable cgroup controllers in {}", path.display()))
+}
+
+fn write_limits(path: &Path, resources: &ResourceConfig) -> Result<()> {
+    let write = |file: &str, value: String| {
+        fs::write(path.join(file), &value)
+            .with_context(|| format!("Failed to set {file} of {} to {value}", path.display()))
+    };
+    if let Some(memory_mb) = resources.memory_mb {
+        write(
+            "memory.max",
+            memory_mb.saturating_mul(1024 * 1024).to_string(),
+        )?;
+        // Swap counts against the cap; absent without swap accounting.
+        let _ = fs::write(path.join("memory.swap.max"), "0");
+    }
+    if let Some(cpus) = resources.cpus {
+        write("cpu.max", cpu_max(cpus))?;
+    }
+    if let Some(pids_limit) = resources.pids_limit {
+        write("pids.max", pids_limit.to_string())?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_own_cgroup() {
+        let proc_cgroup =
+            "0::/user.slice/user-1000.slice/user@1000.service/app.slice/oqto-runner.service\n";
+        assert_eq!(
+            own_cgroup(proc_cgroup),
+            Some("/user.slice/user-1000.slice/user@1000.service/app.slice/oqto-runner.service")
+        );
+        // cgroup v1 only
+        assert_eq!(own_cgroup("12:memory:/user.slice\n"), None);
+    }
+
+    #[test]
+    fn formats_limits() {
+        assert_eq!(cpu_max(1.5), "150000 100000");
+        assert_eq!(cpu_max(0.001), "1000 100000");
+        assert_eq!(leaf_name("oqto-1a2b/../x"), "session-oqto-1a2b____x");
+        assert_eq!(
+            controllers(&ResourceConfig {
+                memory_mb: Some(1024),
+                cpus: None,
+                pids_limit: Some(128),
+            }),
+            vec!["memory", "pids"]
+        );
+    }
+
+    #[test]
+    fn parses_oom_kills() {
+        let events = "low 0\nhigh 0\nmax 12\noom 3\noom_kill 2\noom_group_kill 0\n";
+        assert_eq!(parse_oom_kills(events), 2);
+        assert_eq!(parse_oom_kills(""), 0);
+    }
+}
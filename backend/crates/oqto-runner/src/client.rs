@@ -1,14 +1,17 @@
 //! Runner client for communicating with oqto-runner daemon.
 //!
 //! Provides a high-level async API for spawning and managing processes
-//! through the runner daemon via Unix socket.
+//! through the runner daemon via Unix socket, or over TCP for remote runner
+//! hosts (see [`crate::remote`]). With [`use_grpc_transport`] the same
+//! requests travel over gRPC instead (see [`crate::grpc`]).
 
 use anyhow::{Context, Result};
 use std::collections::HashMap;
 use std::path::{Path, PathBuf};
-use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
-use tokio::net::UnixStream;
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncWrite, AsyncWriteExt, BufReader};
+use tokio::net::{TcpStream, UnixStream};
 
+use crate::grpc::{GrpcClient, GrpcTlsConfig};
 use crate::protocol::*;
 
 /// Timeout for a single runner request (connect + write + read response).
@@ -17,6 +20,50 @@ use crate::protocol::*;
 /// is stuck or overloaded.
 const RUNNER_REQUEST_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(30);
 
+/// Time an archive upload has to be written out.
+const ARCHIVE_EXTRACT_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(300);
+
+/// Observer called after every runner request with the request's wire type,
+/// its duration (including retries) and whether it succeeded.
+pub type RequestObserver = fn(request_type: &str, elapsed: std::time::Duration, ok: bool);
+
+static REQUEST_OBSERVER: std::sync::OnceLock<RequestObserver> = std::sync::OnceLock::new();
+
+/// Install a process-wide observer for runner requests (e.g. for metrics).
+/// Only the first call takes effect.
+pub fn set_request_observer(observer: RequestObserver) {
+    let _ = REQUEST_OBSERVER.set(observer);
+}
+
+/// Wire protocol between backend and runners.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Transport {
+    /// Newline-delimited JSON, one connection per request.
+    #[default]
+    Json,
+    /// gRPC (see [`crate::grpc`]).
+    Grpc,
+}
+
+/// TLS for remote runners, set when clients speak gRPC.
+static GRPC_TRANSPORT: std::sync::OnceLock<GrpcTlsConfig> = std::sync::OnceLock::new();
+
+/// Make every client reach its runner over gRPC, using `tls` for remote
+/// runners, which must then be addressed at their `--grpc-listen` port.
+/// Only the first call takes effect.
+pub fn use_grpc_transport(tls: GrpcTlsConfig) {
+    let _ = GRPC_TRANSPORT.set(tls);
+}
+
+/// Wire type of a request (its serde `type` tag).
+fn request_type(req: &RunnerRequest) -> String {
+    serde_json::to_value(req)
+        .ok()
+        .and_then(|value| value.get("type")?.as_str().map(str::to_string))
+        .unwrap_or_else(|| "unknown".to_string())
+}
+
 /// Default socket path pattern.
 /// Uses XDG_RUNTIME_DIR if available, otherwise falls back to /tmp.
 pub const DEFAULT_SOCKET_PATTERN: &str = "{runtime_dir}/oqto-runner.sock";
@@ -25,10 +72,24 @@ pub const DEFAULT_SOCKET_PATTERN: &str = "{runtime_dir}/oqto-runner.sock";
 /// {uid} is replaced with the user's numeric UID.
 pub const USER_SOCKET_PATTERN: &str = "/run/user/{uid}/oqto-runner.sock";
 
+/// Read half of a runner connection.
+type ConnReader = Box<dyn AsyncRead + Send + Unpin>;
+
+/// Write half of a runner connection.
+type ConnWriter = Box<dyn AsyncWrite + Send + Unpin>;
+
+/// A runner reached over TCP.
+#[derive(Clone)]
+struct RemoteEndpoint {
+    address: String,
+    token: String,
+}
+
 /// Client for communicating with the runner daemon.
 #[derive(Clone)]
 pub struct RunnerClient {
     socket_path: PathBuf,
+    remote: Option<RemoteEndpoint>,
 }
 
 impl RunnerClient {
@@ -36,6 +97,23 @@ impl RunnerClient {
     pub fn new(socket_path: impl Into<PathBuf>) -> Self {
         Self {
             socket_path: socket_path.into(),
+            remote: None,
+        }
+    }
+
+    /// Create a runner client for a remote runner listening on `address`
+    /// (`host:port`), authenticating with `token`.
+    ///
+    /// [`Self::socket_path`] reports `tcp://<address>` for such clients, so
+    /// it still identifies the runner.
+    pub fn tcp(address: impl Into<String>, token: impl Into<String>) -> Self {
+        let address = address.into();
+        Self {
+            socket_path: PathBuf::from(format!("tcp://{address}")),
+            remote: Some(RemoteEndpoint {
+                address,
+                token: token.into(),
+            }),
         }
     }
 
@@ -82,10 +160,90 @@ impl RunnerClient {
     }
 
     fn is_default_socket_path(&self) -> bool {
-        self.socket_path == Self::default().socket_path
+        self.remote.is_none() && self.socket_path == Self::default().socket_path
+    }
+
+    /// Open a connection to the runner, authenticating first on TCP.
+    async fn connect(&self) -> Result<(ConnReader, ConnWriter)> {
+        match &self.remote {
+            Some(remote) => {
+                let stream = TcpStream::connect(&remote.address)
+                    .await
+                    .with_context(|| format!("connecting to runner at {}", remote.address))?;
+                let _ = stream.set_nodelay(true);
+                let (reader, mut writer) = stream.into_split();
+                writer
+                    .write_all(crate::remote::auth_line(&remote.token).as_bytes())
+                    .await
+                    .with_context(|| format!("authenticating with runner at {}", remote.address))?;
+                Ok((Box::new(reader), Box::new(writer)))
+            }
+            None => {
+                let stream = UnixStream::connect(&self.socket_path)
+                    .await
+                    .with_context(|| format!("connecting to runner at {:?}", self.socket_path))?;
+                let (reader, writer) = stream.into_split();
+                Ok((Box::new(reader), Box::new(writer)))
+            }
+        }
+    }
+
+    /// The gRPC client for this runner, when clients speak gRPC.
+    fn grpc(&self) -> Result<Option<GrpcClient>> {
+        let Some(tls) = GRPC_TRANSPORT.get() else {
+            return Ok(None);
+        };
+        let client = match &self.remote {
+            Some(remote) => GrpcClient::tcp(&remote.address, &remote.token, tls)?,
+            None => GrpcClient::unix(&self.socket_path)?,
+        };
+        Ok(Some(client))
+    }
+
+    /// A failed gRPC call as an error worded like its socket counterpart,
+    /// so an unreachable runner is retried the same way.
+    fn grpc_error(&self, status: tonic::Status) -> anyhow::Error {
+        match status.code() {
+            tonic::Code::Unavailable => anyhow::anyhow!("{}", status.message())
+                .context(format!("connecting to runner at {:?}", self.socket_path)),
+            tonic::Code::DeadlineExceeded => anyhow::anyhow!(
+                "runner request timed out after {:?} (socket: {:?})",
+                RUNNER_REQUEST_TIMEOUT,
+                self.socket_path,
+            ),
+            code => anyhow::anyhow!("runner call failed ({:?}): {}", code, status.message()),
+        }
+    }
+
+    /// Send a subscription request and return the stream of responses,
+    /// starting with the confirmation.
+    async fn open_stream(&self, req: RunnerRequest) -> Result<ResponseStream> {
+        if let Some(grpc) = self.grpc()? {
+            let stream = grpc
+                .subscribe(req)
+                .await
+                .map_err(|status| self.grpc_error(status))?;
+            return Ok(ResponseStream::Grpc(stream));
+        }
+
+        let (reader, mut writer) = self.connect().await?;
+        let mut json = serde_json::to_string(&req).context("serializing request")?;
+        json.push('\n');
+        writer
+            .write_all(json.as_bytes())
+            .await
+            .context("writing request")?;
+        Ok(ResponseStream::Lines {
+            lines: BufReader::new(reader).lines(),
+            _writer: writer,
+        })
     }
 
     fn is_transient_connection_error(err: &anyhow::Error) -> bool {
+        // The runner answered, so the connection itself is fine.
+        if err.downcast_ref::<ErrorResponse>().is_some() {
+            return false;
+        }
         err.chain().any(|cause| {
             let msg = cause.to_string();
             msg.contains("Connection refused")
@@ -186,12 +344,24 @@ impl RunnerClient {
         )
     }
 
+    /// Send a request and receive a response, reporting it to the request
+    /// observer if one is installed.
+    async fn request(&self, req: &RunnerRequest) -> Result<RunnerResponse> {
+        let Some(observer) = REQUEST_OBSERVER.get() else {
+            return self.request_with_retries(req).await;
+        };
+        let started = std::time::Instant::now();
+        let result = self.request_with_retries(req).await;
+        observer(&request_type(req), started.elapsed(), result.is_ok());
+        result
+    }
+
     /// Send a request and receive a response.
     ///
     /// Retries transient connection failures (socket not found, permission
     /// denied, connection refused) up to 3 times with 500ms backoff. This
     /// handles brief unavailability during service restarts.
-    async fn request(&self, req: &RunnerRequest) -> Result<RunnerResponse> {
+    async fn request_with_retries(&self, req: &RunnerRequest) -> Result<RunnerResponse> {
         let max_retries = 3;
         let mut last_err = None;
 
@@ -241,20 +411,29 @@ impl RunnerClient {
     }
 
     async fn request_once_inner(&self, req: &RunnerRequest) -> Result<RunnerResponse> {
-        let mut stream = UnixStream::connect(&self.socket_path)
-            .await
-            .with_context(|| format!("connecting to runner at {:?}", self.socket_path))?;
+        if let Some(grpc) = self.grpc()? {
+            let resp = grpc
+                .call(req.clone(), RUNNER_REQUEST_TIMEOUT)
+                .await
+                .map_err(|status| self.grpc_error(status))?;
+            if let RunnerResponse::Error(e) = resp {
+                return Err(e.into());
+            }
+            return Ok(resp);
+        }
+
+        let (reader, mut writer) = self.connect().await?;
 
         // Send request as JSON line
         let mut json = serde_json::to_string(req).context("serializing request")?;
         json.push('\n');
-        stream
+        writer
             .write_all(json.as_bytes())
             .await
             .context("writing request")?;
 
         // Read response line
-        let mut reader = BufReader::new(stream);
+        let mut reader = BufReader::new(reader);
         let mut line = String::new();
         reader
             .read_line(&mut line)
@@ -263,9 +442,10 @@ impl RunnerClient {
 
         let resp: RunnerResponse = serde_json::from_str(&line).context("parsing response")?;
 
-        // Check for error response
-        if let RunnerResponse::Error(e) = &resp {
-            anyhow::bail!("runner error ({:?}): {}", e.code, e.message);
+        // Check for error response. The typed error lets callers match on
+        // `ErrorCode` via `downcast_ref::<ErrorResponse>()`.
+        if let RunnerResponse::Error(e) = resp {
+            return Err(e.into());
         }
 
         Ok(resp)
@@ -377,42 +557,18 @@ impl RunnerClient {
     /// Subscribe to stdout stream. Returns a stream and a reader that should be
     /// used together. The stream yields lines as they arrive from the process.
     pub async fn subscribe_stdout(&self, id: impl Into<String>) -> Result<StdoutSubscription> {
-        let stream = UnixStream::connect(&self.socket_path)
-            .await
-            .with_context(|| format!("connecting to runner at {:?}", self.socket_path))?;
-
-        let process_id = id.into();
-        let req = RunnerRequest::SubscribeStdout(SubscribeStdoutRequest {
-            id: process_id.clone(),
-        });
-
-        let (reader, mut writer) = stream.into_split();
-
-        // Send subscription request
-        let mut json = serde_json::to_string(&req).context("serializing request")?;
-        json.push('\n');
-        writer
-            .write_all(json.as_bytes())
-            .await
-            .context("writing request")?;
+        let req = RunnerRequest::SubscribeStdout(SubscribeStdoutRequest { id: id.into() });
+        let mut stream = self.open_stream(req).await?;
 
         // Read subscription confirmation
-        let reader = BufReader::new(reader);
-        let mut lines = reader.lines();
-
-        let first_line = lines
-            .next_line()
+        let resp = stream
+            .next()
             .await
-            .context("reading subscription response")?
-            .ok_or_else(|| anyhow::anyhow!("connection closed"))?;
-
-        let resp: RunnerResponse = serde_json::from_str(&first_line).context("parsing response")?;
+            .ok_or_else(|| anyhow::anyhow!("connection closed"))?
+            .context("parsing response")?;
 
         match resp {
-            RunnerResponse::StdoutSubscribed(_) => Ok(StdoutSubscription {
-                lines,
-                _writer: writer,
-            }),
+            RunnerResponse::StdoutSubscribed(_) => Ok(StdoutSubscription { stream }),
             RunnerResponse::Error(e) => {
                 anyhow::bail!("runner error ({:?}): {}", e.code, e.message);
             }
@@ -547,6 +703,45 @@ impl RunnerClient {
         }
     }
 
+    /// Extract an archive into the new directory in `req.path`. Sent once:
+    /// a retry would find the directory already there.
+    pub async fn extract_archive(
+        &self,
+        req: ExtractArchiveRequest,
+    ) -> Result<ArchiveExtractedResponse> {
+        let req = RunnerRequest::ExtractArchive(req);
+        let resp = tokio::time::timeout(ARCHIVE_EXTRACT_TIMEOUT, self.request_once_inner(&req))
+            .await
+            .map_err(|_| {
+                anyhow::anyhow!("archive extraction timed out after {ARCHIVE_EXTRACT_TIMEOUT:?}")
+            })??;
+        match resp {
+            RunnerResponse::ArchiveExtracted(r) => Ok(r),
+            _ => anyhow::bail!("unexpected response to extract_archive"),
+        }
+    }
+
+    /// Query or change at-rest encryption of a workspace.
+    ///
+    /// Runner-side failures such as `ErrorCode::WrongPassphrase` are returned
+    /// as an [`ErrorResponse`] inside the error.
+    pub async fn workspace_encryption(
+        &self,
+        workspace_path: impl Into<PathBuf>,
+        action: WorkspaceEncryptionAction,
+    ) -> Result<WorkspaceEncryptionResponse> {
+        let req = RunnerRequest::WorkspaceEncryption(WorkspaceEncryptionRequest {
+            workspace_path: workspace_path.into(),
+            action,
+        });
+
+        let resp = self.request(&req).await?;
+        match resp {
+            RunnerResponse::WorkspaceEncryption(r) => Ok(r),
+            _ => anyhow::bail!("unexpected response to workspace_encryption"),
+        }
+    }
+
     // ========================================================================
     // Session Operations (user-plane)
     // ========================================================================
@@ -585,6 +780,9 @@ impl RunnerClient {
         ttyd_port: u16,
         agent: Option<String>,
         env: HashMap<String, String>,
+        fileserver_token_secret: Option<String>,
+        shell: Option<ShellEnvironment>,
+        secrets: SessionSecrets,
     ) -> Result<SessionStartedResponse> {
         let req = RunnerRequest::StartSession(StartSessionRequest {
             session_id: session_id.into(),
@@ -594,6 +792,9 @@ impl RunnerClient {
             ttyd_port,
             agent,
             env,
+            fileserver_token_secret,
+            shell,
+            secrets,
         });
 
         let resp = self.request(&req).await?;
@@ -619,6 +820,22 @@ impl RunnerClient {
         }
     }
 
+    /// Sample the resource usage of a session's processes.
+    pub async fn get_session_resources(
+        &self,
+        session_id: impl Into<String>,
+    ) -> Result<SessionResourcesResponse> {
+        let req = RunnerRequest::GetSessionResources(GetSessionResourcesRequest {
+            session_id: session_id.into(),
+        });
+
+        let resp = self.request(&req).await?;
+        match resp {
+            RunnerResponse::SessionResources(r) => Ok(r),
+            _ => anyhow::bail!("unexpected response to get_session_resources"),
+        }
+    }
+
     // ========================================================================
     // Main Chat Operations (user-plane)
     // ========================================================================
@@ -750,6 +967,26 @@ impl RunnerClient {
         }
     }
 
+    /// Check the integrity of the user's hstry database, optionally snapshotting it.
+    pub async fn check_history_integrity(
+        &self,
+        full: bool,
+        backup: bool,
+        keep_backups: usize,
+    ) -> Result<HistoryIntegrityResponse> {
+        let req = RunnerRequest::CheckHistoryIntegrity(CheckHistoryIntegrityRequest {
+            full,
+            backup,
+            keep_backups,
+        });
+
+        let resp = self.request(&req).await?;
+        match resp {
+            RunnerResponse::HistoryIntegrity(r) => Ok(r),
+            _ => anyhow::bail!("unexpected response to check_history_integrity"),
+        }
+    }
+
     // ========================================================================
     // Memory Operations (user-plane)
     // ========================================================================
@@ -988,6 +1225,17 @@ impl RunnerClient {
             .await
     }
 
+    /// Give the agent guidance mid-turn (steered live or queued for the
+    /// next turn, depending on the harness).
+    pub async fn agent_interject(
+        &self,
+        session_id: &str,
+        message: &str,
+        client_id: Option<String>,
+    ) -> Result<PiInterjectedResponse> {
+        self.pi_interject(session_id, message, client_id).await
+    }
+
     /// Abort the current operation.
     pub async fn agent_abort(&self, session_id: &str) -> Result<()> {
         self.pi_abort(session_id).await
@@ -1210,6 +1458,26 @@ impl RunnerClient {
         }
     }
 
+    /// Give a session guidance mid-turn. Returns how it is delivered.
+    pub async fn pi_interject(
+        &self,
+        session_id: &str,
+        message: &str,
+        client_id: Option<String>,
+    ) -> Result<PiInterjectedResponse> {
+        let req = RunnerRequest::PiInterject(PiInterjectRequest {
+            session_id: session_id.to_string(),
+            message: message.to_string(),
+            client_id,
+        });
+
+        let resp = self.request(&req).await?;
+        match resp {
+            RunnerResponse::PiInterjected(r) => Ok(r),
+            _ => anyhow::bail!("unexpected response to pi_interject"),
+        }
+    }
+
     /// Abort the current Pi session operation.
     pub async fn pi_abort(&self, session_id: &str) -> Result<()> {
         let req = RunnerRequest::PiAbort(PiAbortRequest {
@@ -1243,7 +1511,7 @@ impl RunnerClient {
         // Timeout for the connect + handshake phase only. Once the subscription
         // is established, the streaming read loop runs without a global timeout
         // (individual events have their own semantics).
-        let (lines, session_id, resp) = tokio::time::timeout(
+        let (stream, session_id, resp) = tokio::time::timeout(
             RUNNER_REQUEST_TIMEOUT,
             self.pi_subscribe_handshake(session_id),
         )
@@ -1257,11 +1525,7 @@ impl RunnerClient {
         })??;
 
         match resp {
-            RunnerResponse::PiSubscribed(_) => Ok(PiSubscription {
-                session_id,
-                lines: lines.0,
-                _writer: lines.1,
-            }),
+            RunnerResponse::PiSubscribed(_) => Ok(PiSubscription { session_id, stream }),
             RunnerResponse::Error(e) => {
                 anyhow::bail!("runner error ({:?}): {}", e.code, e.message);
             }
@@ -1273,46 +1537,21 @@ impl RunnerClient {
     async fn pi_subscribe_handshake(
         &self,
         session_id: &str,
-    ) -> Result<(
-        (
-            tokio::io::Lines<BufReader<tokio::net::unix::OwnedReadHalf>>,
-            tokio::net::unix::OwnedWriteHalf,
-        ),
-        String,
-        RunnerResponse,
-    )> {
-        let stream = UnixStream::connect(&self.socket_path)
-            .await
-            .with_context(|| format!("connecting to runner at {:?}", self.socket_path))?;
-
+    ) -> Result<(ResponseStream, String, RunnerResponse)> {
         let session_id = session_id.to_string();
         let req = RunnerRequest::PiSubscribe(PiSubscribeRequest {
             session_id: session_id.clone(),
         });
-
-        let (reader, mut writer) = stream.into_split();
-
-        // Send subscription request
-        let mut json = serde_json::to_string(&req).context("serializing request")?;
-        json.push('\n');
-        writer
-            .write_all(json.as_bytes())
-            .await
-            .context("writing request")?;
+        let mut stream = self.open_stream(req).await?;
 
         // Read subscription confirmation
-        let reader = BufReader::new(reader);
-        let mut lines = reader.lines();
-
-        let first_line = lines
-            .next_line()
+        let resp = stream
+            .next()
             .await
-            .context("reading subscription response")?
-            .ok_or_else(|| anyhow::anyhow!("connection closed"))?;
+            .ok_or_else(|| anyhow::anyhow!("connection closed"))?
+            .context("parsing response")?;
 
-        let resp: RunnerResponse = serde_json::from_str(&first_line).context("parsing response")?;
-
-        Ok(((lines, writer), session_id, resp))
+        Ok((stream, session_id, resp))
     }
 
     /// Unsubscribe from a Pi session's events.
@@ -1714,6 +1953,368 @@ impl RunnerClient {
         }
     }
 
+    // ========================================================================
+    // Harness Diagnostics
+    // ========================================================================
+
+    /// List crash bundles, optionally for one session.
+    pub async fn list_crash_bundles(
+        &self,
+        session_id: Option<&str>,
+    ) -> Result<CrashBundleListResponse> {
+        let req = RunnerRequest::ListCrashBundles(ListCrashBundlesRequest {
+            session_id: session_id.map(str::to_string),
+        });
+
+        let resp = self.request(&req).await?;
+        match resp {
+            RunnerResponse::CrashBundleList(r) => Ok(r),
+            _ => anyhow::bail!("unexpected response to list_crash_bundles"),
+        }
+    }
+
+    /// Fetch a complete crash bundle.
+    pub async fn get_crash_bundle(&self, bundle_id: &str) -> Result<CrashBundle> {
+        let req = RunnerRequest::GetCrashBundle(GetCrashBundleRequest {
+            bundle_id: bundle_id.to_string(),
+        });
+
+        let resp = self.request(&req).await?;
+        match resp {
+            RunnerResponse::CrashBundle(r) => Ok(*r),
+            _ => anyhow::bail!("unexpected response to get_crash_bundle"),
+        }
+    }
+
+    // ========================================================================
+    // Agent Artifacts
+    // ========================================================================
+
+    /// List images captured for a session.
+    pub async fn list_artifacts(&self, session_id: &str) -> Result<ArtifactListResponse> {
+        let req = RunnerRequest::ListArtifacts(ListArtifactsRequest {
+            session_id: session_id.to_string(),
+        });
+
+        let resp = self.request(&req).await?;
+        match resp {
+            RunnerResponse::ArtifactList(r) => Ok(r),
+            _ => anyhow::bail!("unexpected response to list_artifacts"),
+        }
+    }
+
+    /// Fetch an artifact (or its thumbnail).
+    pub async fn get_artifact(
+        &self,
+        artifact_id: &str,
+        thumbnail: bool,
+    ) -> Result<ArtifactResponse> {
+        let req = RunnerRequest::GetArtifact(GetArtifactRequest {
+            artifact_id: artifact_id.to_string(),
+            thumbnail,
+        });
+
+        let resp = self.request(&req).await?;
+        match resp {
+            RunnerResponse::Artifact(r) => Ok(*r),
+            _ => anyhow::bail!("unexpected response to get_artifact"),
+        }
+    }
+
+    // ========================================================================
+    // Background Processes
+    // ========================================================================
+
+    /// Start a supervised background process for a session.
+    pub async fn start_background_process(
+        &self,
+        req: StartBackgroundProcessRequest,
+    ) -> Result<BackgroundProcessInfo> {
+        let resp = self
+            .request(&RunnerRequest::StartBackgroundProcess(req))
+            .await?;
+        match resp {
+            RunnerResponse::BackgroundProcess(r) => Ok(r),
+            _ => anyhow::bail!("unexpected response to start_background_process"),
+        }
+    }
+
+    /// List background processes, optionally for one session.
+    pub async fn list_background_processes(
+        &self,
+        session_id: Option<&str>,
+    ) -> Result<BackgroundProcessListResponse> {
+        let req = RunnerRequest::ListBackgroundProcesses(ListBackgroundProcessesRequest {
+            session_id: session_id.map(ToOwned::to_owned),
+        });
+
+        let resp = self.request(&req).await?;
+        match resp {
+            RunnerResponse::BackgroundProcessList(r) => Ok(r),
+            _ => anyhow::bail!("unexpected response to list_background_processes"),
+        }
+    }
+
+    /// Stop a background process.
+    pub async fn stop_background_process(&self, id: &str) -> Result<BackgroundProcessInfo> {
+        let req = RunnerRequest::StopBackgroundProcess(StopBackgroundProcessRequest {
+            id: id.to_string(),
+        });
+
+        let resp = self.request(&req).await?;
+        match resp {
+            RunnerResponse::BackgroundProcess(r) => Ok(r),
+            _ => anyhow::bail!("unexpected response to stop_background_process"),
+        }
+    }
+
+    /// Recent output of a background process.
+    pub async fn get_background_process_logs(
+        &self,
+        id: &str,
+        lines: Option<usize>,
+    ) -> Result<BackgroundProcessLogsResponse> {
+        let req = RunnerRequest::GetBackgroundProcessLogs(GetBackgroundProcessLogsRequest {
+            id: id.to_string(),
+            lines,
+        });
+
+        let resp = self.request(&req).await?;
+        match resp {
+            RunnerResponse::BackgroundProcessLogs(r) => Ok(r),
+            _ => anyhow::bail!("unexpected response to get_background_process_logs"),
+        }
+    }
+
+    // ========================================================================
+    // Dependency Scans
+    // ========================================================================
+
+    /// Run vulnerability scanners over a workspace. Unlike other requests
+    /// this waits as long as the scanners may take.
+    pub async fn scan_dependencies(
+        &self,
+        workspace_path: impl Into<PathBuf>,
+        scanners: Vec<DependencyScanner>,
+        timeout_secs: Option<u64>,
+    ) -> Result<DependencyScanResponse> {
+        let scanner_count = scanners.len().max(1) as u32;
+        let req = RunnerRequest::ScanDependencies(ScanDependenciesRequest {
+            workspace_path: workspace_path.into(),
+            scanners,
+            timeout_secs,
+        });
+        let budget = crate::dependency_scan::effective_timeout(timeout_secs) * scanner_count
+            + RUNNER_REQUEST_TIMEOUT;
+
+        let resp = tokio::time::timeout(budget, self.request_once_inner(&req))
+            .await
+            .map_err(|_| anyhow::anyhow!("dependency scan timed out after {:?}", budget))??;
+        match resp {
+            RunnerResponse::DependencyScan(r) => Ok(r),
+            _ => anyhow::bail!("unexpected response to scan_dependencies"),
+        }
+    }
+
+    // ========================================================================
+    // File History
+    // ========================================================================
+
+    /// Versions of a workspace file, newest first.
+    pub async fn list_file_versions(
+        &self,
+        workspace_path: impl Into<PathBuf>,
+        path: &str,
+        limit: Option<usize>,
+    ) -> Result<FileVersionsResponse> {
+        let req = RunnerRequest::ListFileVersions(ListFileVersionsRequest {
+            workspace_path: workspace_path.into(),
+            path: path.to_string(),
+            limit,
+        });
+
+        let resp = self.request(&req).await?;
+        match resp {
+            RunnerResponse::FileVersions(r) => Ok(r),
+            _ => anyhow::bail!("unexpected response to list_file_versions"),
+        }
+    }
+
+    /// A workspace file's content at an earlier version.
+    pub async fn read_file_version(
+        &self,
+        workspace_path: impl Into<PathBuf>,
+        path: &str,
+        selector: FileVersionSelector,
+    ) -> Result<FileVersionContentResponse> {
+        let req = RunnerRequest::ReadFileVersion(ReadFileVersionRequest {
+            workspace_path: workspace_path.into(),
+            path: path.to_string(),
+            selector,
+        });
+
+        let resp = self.request(&req).await?;
+        match resp {
+            RunnerResponse::FileVersionContent(r) => Ok(r),
+            _ => anyhow::bail!("unexpected response to read_file_version"),
+        }
+    }
+
+    // ========================================================================
+    // Git
+    // ========================================================================
+
+    /// Branch and changed files of the workspace repository.
+    pub async fn git_status(
+        &self,
+        workspace_path: impl Into<PathBuf>,
+    ) -> Result<GitStatusResponse> {
+        let req = RunnerRequest::GitStatus(GitStatusRequest {
+            workspace_path: workspace_path.into(),
+        });
+
+        let resp = self.request(&req).await?;
+        match resp {
+            RunnerResponse::GitStatus(r) => Ok(r),
+            _ => anyhow::bail!("unexpected response to git_status"),
+        }
+    }
+
+    /// Diff of uncommitted changes, or of `commit`.
+    pub async fn git_diff(
+        &self,
+        workspace_path: impl Into<PathBuf>,
+        path: Option<String>,
+        staged: bool,
+        commit: Option<String>,
+    ) -> Result<GitDiffResponse> {
+        let req = RunnerRequest::GitDiff(GitDiffRequest {
+            workspace_path: workspace_path.into(),
+            path,
+            staged,
+            commit,
+        });
+
+        let resp = self.request(&req).await?;
+        match resp {
+            RunnerResponse::GitDiff(r) => Ok(r),
+            _ => anyhow::bail!("unexpected response to git_diff"),
+        }
+    }
+
+    /// Commits of the workspace repository, newest first.
+    pub async fn git_log(
+        &self,
+        workspace_path: impl Into<PathBuf>,
+        limit: Option<usize>,
+        path: Option<String>,
+        rev: Option<String>,
+    ) -> Result<GitLogResponse> {
+        let req = RunnerRequest::GitLog(GitLogRequest {
+            workspace_path: workspace_path.into(),
+            limit,
+            path,
+            rev,
+        });
+
+        let resp = self.request(&req).await?;
+        match resp {
+            RunnerResponse::GitLog(r) => Ok(r),
+            _ => anyhow::bail!("unexpected response to git_log"),
+        }
+    }
+
+    /// Local branches of the workspace repository.
+    pub async fn git_branches(
+        &self,
+        workspace_path: impl Into<PathBuf>,
+    ) -> Result<GitBranchesResponse> {
+        let req = RunnerRequest::GitBranches(GitBranchesRequest {
+            workspace_path: workspace_path.into(),
+        });
+
+        let resp = self.request(&req).await?;
+        match resp {
+            RunnerResponse::GitBranches(r) => Ok(r),
+            _ => anyhow::bail!("unexpected response to git_branches"),
+        }
+    }
+
+    /// Check out branch `name`, creating it with `create`. Returns the
+    /// branches afterwards.
+    pub async fn git_switch_branch(
+        &self,
+        workspace_path: impl Into<PathBuf>,
+        name: &str,
+        create: bool,
+    ) -> Result<GitBranchesResponse> {
+        let req = RunnerRequest::GitSwitchBranch(GitSwitchBranchRequest {
+            workspace_path: workspace_path.into(),
+            name: name.to_string(),
+            create,
+        });
+
+        let resp = self.request(&req).await?;
+        match resp {
+            RunnerResponse::GitBranches(r) => Ok(r),
+            _ => anyhow::bail!("unexpected response to git_switch_branch"),
+        }
+    }
+
+    /// Stage and commit changes.
+    pub async fn git_commit(&self, req: GitCommitRequest) -> Result<GitCommitInfo> {
+        let resp = self.request(&RunnerRequest::GitCommit(req)).await?;
+        match resp {
+            RunnerResponse::GitCommitted(r) => Ok(r),
+            _ => anyhow::bail!("unexpected response to git_commit"),
+        }
+    }
+
+    /// Push a branch of the workspace repository.
+    pub async fn git_push(&self, req: GitPushRequest) -> Result<GitPushResponse> {
+        let resp = self.request(&RunnerRequest::GitPush(req)).await?;
+        match resp {
+            RunnerResponse::GitPushed(r) => Ok(r),
+            _ => anyhow::bail!("unexpected response to git_push"),
+        }
+    }
+
+    // ========================================================================
+    // Data Queries
+    // ========================================================================
+
+    /// Run a read-only SQL query over data files in a workspace.
+    pub async fn query_data(&self, req: DataQueryRequest) -> Result<DataQueryResponse> {
+        let budget =
+            crate::data_query::effective_timeout(req.timeout_secs) + RUNNER_REQUEST_TIMEOUT;
+        let req = RunnerRequest::QueryData(req);
+        let resp = tokio::time::timeout(budget, self.request_once_inner(&req))
+            .await
+            .map_err(|_| anyhow::anyhow!("data query timed out after {:?}", budget))??;
+        match resp {
+            RunnerResponse::DataQuery(r) => Ok(r),
+            _ => anyhow::bail!("unexpected response to query_data"),
+        }
+    }
+
+    // ========================================================================
+    // Template Tests
+    // ========================================================================
+
+    /// Run a template's smoke check in a project created from it.
+    pub async fn run_smoke_check(&self, req: SmokeCheckRequest) -> Result<SmokeCheckResponse> {
+        let budget =
+            crate::smoke_check::effective_timeout(req.timeout_secs) + RUNNER_REQUEST_TIMEOUT;
+        let req = RunnerRequest::RunSmokeCheck(req);
+        let resp = tokio::time::timeout(budget, self.request_once_inner(&req))
+            .await
+            .map_err(|_| anyhow::anyhow!("smoke check timed out after {:?}", budget))??;
+        match resp {
+            RunnerResponse::SmokeCheck(r) => Ok(r),
+            _ => anyhow::bail!("unexpected response to run_smoke_check"),
+        }
+    }
+
     /// Send response to an extension UI request.
     pub async fn pi_extension_ui_response(
         &self,
@@ -1737,6 +2338,37 @@ impl RunnerClient {
             _ => anyhow::bail!("unexpected response to pi_extension_ui_response"),
         }
     }
+
+    /// Tool calls of a session waiting for approval, oldest first.
+    pub async fn list_tool_approvals(&self, session_id: &str) -> Result<Vec<ToolApproval>> {
+        let req = RunnerRequest::ListToolApprovals(ListToolApprovalsRequest {
+            session_id: session_id.to_string(),
+        });
+        match self.request(&req).await? {
+            RunnerResponse::ToolApprovalList(r) => Ok(r.approvals),
+            _ => anyhow::bail!("unexpected response to list_tool_approvals"),
+        }
+    }
+
+    /// Approve or deny a tool call waiting for approval.
+    pub async fn resolve_tool_approval(
+        &self,
+        session_id: &str,
+        approval_id: &str,
+        approved: bool,
+        reason: Option<String>,
+    ) -> Result<ToolApproval> {
+        let req = RunnerRequest::ResolveToolApproval(ResolveToolApprovalRequest {
+            session_id: session_id.to_string(),
+            approval_id: approval_id.to_string(),
+            approved,
+            reason,
+        });
+        match self.request(&req).await? {
+            RunnerResponse::ToolApprovalResolved(r) => Ok(r),
+            _ => anyhow::bail!("unexpected response to resolve_tool_approval"),
+        }
+    }
 }
 
 /// Create a runner client using the default socket path.
@@ -1751,35 +2383,50 @@ impl Default for RunnerClient {
     }
 }
 
+/// Responses to a subscription, read from a connection or a gRPC stream.
+enum ResponseStream {
+    Lines {
+        lines: tokio::io::Lines<BufReader<ConnReader>>,
+        // Keep writer alive to maintain connection
+        _writer: ConnWriter,
+    },
+    Grpc(tonic::Streaming<RunnerResponse>),
+}
+
+impl ResponseStream {
+    /// The next response: None once the stream ended or broke, Some(Err)
+    /// for a line that is not a response.
+    async fn next(&mut self) -> Option<serde_json::Result<RunnerResponse>> {
+        match self {
+            Self::Lines { lines, .. } => {
+                let line = lines.next_line().await.ok().flatten()?;
+                Some(serde_json::from_str(&line))
+            }
+            Self::Grpc(stream) => stream.message().await.ok().flatten().map(Ok),
+        }
+    }
+}
+
 /// An active stdout subscription that yields lines as they arrive.
 pub struct StdoutSubscription {
-    lines: tokio::io::Lines<BufReader<tokio::net::unix::OwnedReadHalf>>,
-    // Keep writer alive to maintain connection
-    _writer: tokio::net::unix::OwnedWriteHalf,
+    stream: ResponseStream,
 }
 
 impl StdoutSubscription {
     /// Read the next event from the subscription.
     /// Returns None when the subscription ends (process exited or connection closed).
     pub async fn next(&mut self) -> Option<StdoutSubscriptionEvent> {
-        match self.lines.next_line().await {
-            Ok(Some(line)) => {
-                match serde_json::from_str::<RunnerResponse>(&line) {
-                    Ok(RunnerResponse::StdoutLine(l)) => {
-                        Some(StdoutSubscriptionEvent::Line(l.line))
-                    }
-                    Ok(RunnerResponse::StdoutEnd(_e)) => Some(StdoutSubscriptionEvent::End),
-                    Ok(_) => {
-                        // Unexpected response, skip
-                        None
-                    }
-                    Err(_) => {
-                        // Parse error, skip
-                        None
-                    }
-                }
+        match self.stream.next().await? {
+            Ok(RunnerResponse::StdoutLine(l)) => Some(StdoutSubscriptionEvent::Line(l.line)),
+            Ok(RunnerResponse::StdoutEnd(_e)) => Some(StdoutSubscriptionEvent::End),
+            Ok(_) => {
+                // Unexpected response, skip
+                None
+            }
+            Err(_) => {
+                // Parse error, skip
+                None
             }
-            Ok(None) | Err(_) => None,
         }
     }
 }
@@ -1796,9 +2443,7 @@ pub enum StdoutSubscriptionEvent {
 /// An active Pi event subscription that yields events as they arrive.
 pub struct PiSubscription {
     session_id: String,
-    lines: tokio::io::Lines<BufReader<tokio::net::unix::OwnedReadHalf>>,
-    // Keep writer alive to maintain connection
-    _writer: tokio::net::unix::OwnedWriteHalf,
+    stream: ResponseStream,
 }
 
 impl PiSubscription {
@@ -1810,28 +2455,26 @@ impl PiSubscription {
     /// Read the next event from the subscription.
     /// Returns None when the subscription ends (session closed or connection lost).
     pub async fn next(&mut self) -> Option<PiSubscriptionEvent> {
-        match self.lines.next_line().await {
-            Ok(Some(line)) => match serde_json::from_str::<RunnerResponse>(&line) {
-                Ok(RunnerResponse::PiEvent(canonical_event)) => {
-                    Some(PiSubscriptionEvent::Event(Box::new(canonical_event)))
-                }
-                Ok(RunnerResponse::PiSubscriptionEnd(end)) => {
-                    Some(PiSubscriptionEvent::End { reason: end.reason })
-                }
-                Ok(RunnerResponse::Error(e)) => Some(PiSubscriptionEvent::Error {
-                    code: e.code,
-                    message: e.message,
-                }),
-                Ok(_) => {
-                    // Unexpected response, skip and continue
-                    None
-                }
-                Err(_) => {
-                    // Parse error, skip and continue
-                    None
-                }
-            },
-            Ok(None) | Err(_) => Some(PiSubscriptionEvent::End {
+        match self.stream.next().await {
+            Some(Ok(RunnerResponse::PiEvent(canonical_event))) => {
+                Some(PiSubscriptionEvent::Event(Box::new(canonical_event)))
+            }
+            Some(Ok(RunnerResponse::PiSubscriptionEnd(end))) => {
+                Some(PiSubscriptionEvent::End { reason: end.reason })
+            }
+            Some(Ok(RunnerResponse::Error(e))) => Some(PiSubscriptionEvent::Error {
+                code: e.code,
+                message: e.message,
+            }),
+            Some(Ok(_)) => {
+                // Unexpected response, skip and continue
+                None
+            }
+            Some(Err(_)) => {
+                // Parse error, skip and continue
+                None
+            }
+            None => Some(PiSubscriptionEvent::End {
                 reason: "connection_closed".to_string(),
             }),
         }
@@ -2013,6 +2656,7 @@ mod tests {
                             session_id: req.session_id,
                             runner_id: "local".to_string(),
                             ts: 1,
+                            seq: None,
                             payload: oqto_protocol::events::EventPayload::StreamTextDelta {
                                 message_id: "msg-1".to_string(),
                                 delta: "hello".to_string(),
@@ -2239,6 +2883,9 @@ mod security_tests {
                 41822,
                 None,
                 std::collections::HashMap::new(),
+                None,
+                None,
+                SessionSecrets::default(),
             )
             .await;
 
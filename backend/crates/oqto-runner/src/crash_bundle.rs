@@ -0,0 +1,544 @@
+//! Forensic bundles for harness processes that exit abnormally.
+//!
+//! When a Pi process exits non-zero (or is killed by a signal) without the
+//! runner asking it to stop, the runner writes a bundle with the stderr
+//! tail, the tail of the harness session file, core dump details and an
+//! environment fingerprint with secret values redacted. Bundles live under
+//! the runner's state directory (`<state>/oqto/crash-bundles/<id>/`) and are
+//! served to the backend through `ListCrashBundles` / `GetCrashBundle`.
+
+use std::collections::{BTreeMap, HashMap};
+use std::os::unix::process::ExitStatusExt;
+use std::path::{Path, PathBuf};
+use std::process::ExitStatus;
+use std::time::{Duration, SystemTime};
+
+use anyhow::{Context, Result};
+use base64::Engine;
+use log::{debug, info, warn};
+use tokio::io::{AsyncReadExt, AsyncSeekExt};
+
+use crate::protocol::{
+    CoreDumpInfo, CrashBundle, CrashBundleFile, CrashBundleSummary, EnvFingerprint,
+};
+
+/// Bundles kept per runner; older ones are pruned after each collection.
+pub const DEFAULT_KEEP_BUNDLES: usize = 20;
+
+/// Session file tail captured into a bundle.
+const MAX_SESSION_FILE_BYTES: u64 = 1024 * 1024;
+
+/// Core files up to this size are embedded in the bundle; larger ones are
+/// only referenced by path.
+const MAX_INLINE_CORE_BYTES: u64 = 8 * 1024 * 1024;
+
+/// `coredumpctl info` output kept in a bundle.
+const MAX_COREDUMP_DETAILS_BYTES: usize = 64 * 1024;
+
+/// Core files older than this are assumed to belong to an earlier crash.
+const CORE_FILE_MAX_AGE: Duration = Duration::from_secs(300);
+
+const REDACTED: &str = "<redacted>";
+
+/// Key fragments that mark an environment variable as secret.
+const SECRET_KEY_MARKERS: &[&str] = &[
+    "KEY",
+    "TOKEN",
+    "SECRET",
+    "PASSWORD",
+    "PASSWD",
+    "CREDENTIAL",
+    "AUTH",
+    "COOKIE",
+    "PRIVATE",
+];
+
+/// Whether `key` names a variable whose value must not leave the machine.
+pub fn is_secret_env_key(key: &str) -> bool {
+    let upper = key.to_ascii_uppercase();
+    SECRET_KEY_MARKERS.iter().any(|m| upper.contains(m))
+}
+
+/// Redact secret-looking values.
+pub fn redact_env<I>(vars: I) -> BTreeMap<String, String>
+where
+    I: IntoIterator<Item = (String, String)>,
+{
+    vars.into_iter()
+        .map(|(k, v)| {
+            let v = if is_secret_env_key(&k) {
+                REDACTED.to_string()
+            } else {
+                v
+            };
+            (k, v)
+        })
+        .collect()
+}
+
+/// What the runner knew about a harness process when it spawned it.
+#[derive(Debug, Clone)]
+pub struct CrashContext {
+    pub harness: String,
+    pub binary: PathBuf,
+    pub cwd: PathBuf,
+    pub sandboxed: bool,
+    pub pid: Option<u32>,
+    /// Environment the process was started with, already redacted.
+    pub env: BTreeMap<String, String>,
+}
+
+impl CrashContext {
+    /// Capture the runner environment overlaid with the session's explicit
+    /// variables, as the spawned process sees it.
+    pub fn new(
+        harness: &str,
+        binary: &Path,
+        cwd: &Path,
+        sandboxed: bool,
+        pid: Option<u32>,
+        session_env: &HashMap<String, String>,
+    ) -> Self {
+        let mut env: HashMap<String, String> = std::env::vars().collect();
+        env.extend(session_env.iter().map(|(k, v)| (k.clone(), v.clone())));
+        Self {
+            harness: harness.to_string(),
+            binary: binary.to_path_buf(),
+            cwd: cwd.to_path_buf(),
+            sandboxed,
+            pid,
+            env: redact_env(env),
+        }
+    }
+}
+
+/// Whether an exit status warrants a bundle.
+pub fn is_abnormal_exit(status: &ExitStatus) -> bool {
+    !status.success()
+}
+
+/// On-disk crash bundle store.
+#[derive(Debug, Clone)]
+pub struct CrashBundleStore {
+    dir: PathBuf,
+    keep: usize,
+}
+
+impl CrashBundleStore {
+    pub fn new(dir: PathBuf, keep: usize) -> Self {
+        Self {
+            dir,
+            keep: keep.max(1),
+        }
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Collect and persist a bundle for a crashed harness.
+    pub async fn collect(
+        &self,
+        session_id: &str,
+        pi_session_id: Option<&str>,
+        status: ExitStatus,
+        stderr: Vec<String>,
+        session_file: Option<&Path>,
+        ctx: &CrashContext,
+    ) -> Result<CrashBundleSummary> {
+        let collected_at = chrono::Utc::now();
+        let id = format!(
+            "crash-{}-{}",
+            collected_at.format("%Y%m%dT%H%M%SZ"),
+            &uuid::Uuid::new_v4().simple().to_string()[..8]
+        );
+
+        let core_pattern = tokio::fs::read_to_string("/proc/sys/kernel/core_pattern")
+            .await
+            .ok()
+            .map(|s| s.trim().to_string());
+        let core_dump = match ctx.pid {
+            Some(pid) => find_core_dump(pid, &ctx.cwd, core_pattern.as_deref()).await,
+            None => None,
+        };
+        let session_file = match session_file {
+            Some(path) => read_tail(path, MAX_SESSION_FILE_BYTES).await,
+            None => None,
+        };
+
+        let summary = CrashBundleSummary {
+            id: id.clone(),
+            session_id: session_id.to_string(),
+            pi_session_id: pi_session_id
+                .filter(|s| !s.is_empty() && *s != session_id)
+                .map(str::to_string),
+            harness: ctx.harness.clone(),
+            collected_at: collected_at.to_rfc3339_opts(chrono::SecondsFormat::Millis, true),
+            exit_code: status.code(),
+            signal: status.signal(),
+            has_core_dump: core_dump.is_some(),
+            last_stderr: stderr.last().cloned(),
+        };
+        let bundle = CrashBundle {
+            summary: summary.clone(),
+            stderr,
+            session_file,
+            core_dump,
+            environment: EnvFingerprint {
+                runner_version: env!("CARGO_PKG_VERSION").to_string(),
+                os_release: tokio::fs::read_to_string("/proc/sys/kernel/osrelease")
+                    .await
+                    .ok()
+                    .map(|s| s.trim().to_string()),
+                arch: std::env::consts::ARCH.to_string(),
+                binary: ctx.binary.display().to_string(),
+                cwd: ctx.cwd.display().to_string(),
+                sandboxed: ctx.sandboxed,
+                pid: ctx.pid,
+                core_pattern,
+                core_limit: core_limit(),
+                vars: ctx.env.clone(),
+            },
+        };
+
+        let bundle_dir = self.dir.join(&id);
+        tokio::fs::create_dir_all(&bundle_dir)
+            .await
+            .with_context(|| format!("creating {}", bundle_dir.display()))?;
+        write_private(
+            &bundle_dir.join("bundle.json"),
+            &serde_json::to_vec(&bundle).context("serializing crash bundle")?,
+        )
+        .await?;
+        write_private(
+            &bundle_dir.join("summary.json"),
+            &serde_json::to_vec(&summary).context("serializing crash bundle summary")?,
+        )
+        .await?;
+
+        info!(
+            "Collected crash bundle {} for session '{}' (exit_code={:?}, signal={:?}, core={})",
+            id, session_id, summary.exit_code, summary.signal, summary.has_core_dump
+        );
+        if let Err(e) = self.prune().await {
+            warn!("Failed to prune crash bundles: {:#}", e);
+        }
+        Ok(summary)
+    }
+
+    /// Bundle summaries, newest first. `session_id` matches either the runner
+    /// session ID or the Pi native ID.
+    pub async fn list(&self, session_id: Option<&str>) -> Result<Vec<CrashBundleSummary>> {
+        let mut bundles = Vec::new();
+        let mut entries = match tokio::fs::read_dir(&self.dir).await {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(bundles),
+            Err(e) => return Err(e).with_context(|| format!("reading {}", self.dir.display())),
+        };
+        while let Some(entry) = entries.next_entry().await? {
+            let path = entry.path().join("summary.json");
+            let Ok(raw) = tokio::fs::read(&path).await else {
+                continue;
+            };
+            match serde_json::from_slice::<CrashBundleSummary>(&raw) {
+                Ok(summary) => {
+                    if session_id.is_none_or(|sid| {
+                        summary.session_id == sid || summary.pi_session_id.as_deref() == Some(sid)
+                    }) {
+                        bundles.push(summary);
+                    }
+                }
+                Err(e) => debug!("Skipping unreadable {}: {}", path.display(), e),
+            }
+        }
+        bundles.sort_by(|a, b| b.collected_at.cmp(&a.collected_at));
+        Ok(bundles)
+    }
+
+    /// Load a complete bundle.
+    pub async fn get(&self, bundle_id: &str) -> Result<Option<CrashBundle>> {
+        if !is_valid_bundle_id(bundle_id) {
+            anyhow::bail!("invalid crash bundle id: {bundle_id}");
+        }
+        let path = self.dir.join(bundle_id).join("bundle.json");
+        let raw = match tokio::fs::read(&path).await {
+            Ok(raw) => raw,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e).with_context(|| format!("reading {}", path.display())),
+        };
+        serde_json::from_slice(&raw)
+            .map(Some)
+            .with_context(|| format!("parsing {}", path.display()))
+    }
+
+    async fn prune(&self) -> Result<()> {
+        let bundles = self.list(None).await?;
+        for stale in bundles.iter().skip(self.keep) {
+            let dir = self.dir.join(&stale.id);
+            tokio::fs::remove_dir_all(&dir)
+                .await
+                .with_context(|| format!("removing {}", dir.display()))?;
+        }
+        Ok(())
+    }
+}
+
+fn is_valid_bundle_id(id: &str) -> bool {
+    id.starts_with("crash-") && id.chars().all(|c| c.is_ascii_alphanumeric() || c == '-')
+}
+
+pub(crate) async fn write_private(path: &Path, data: &[u8]) -> Result<()> {
+    use std::os::unix::fs::OpenOptionsExt;
+    use tokio::io::AsyncWriteExt;
+
+    let mut file = tokio::fs::OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .mode(0o600)
+        .open(path)
+        .await
+        .with_context(|| format!("creating {}", path.display()))?;
+    file.write_all(data)
+        .await
+        .with_context(|| format!("writing {}", path.display()))?;
+    Ok(())
+}
+
+/// Read at most the last `max_bytes` of a file, starting at a line boundary
+/// when truncated.
+async fn read_tail(path: &Path, max_bytes: u64) -> Option<CrashBundleFile> {
+    let mut file = tokio::fs::File::open(path).await.ok()?;
+    let size = file.metadata().await.ok()?.len();
+    let truncated = size > max_bytes;
+    if truncated {
+        file.seek(std::io::SeekFrom::Start(size - max_bytes))
+            .await
+            .ok()?;
+    }
+    let mut buf = Vec::with_capacity(size.min(max_bytes) as usize);
+    file.read_to_end(&mut buf).await.ok()?;
+    let mut content = String::from_utf8_lossy(&buf).into_owned();
+    if truncated && let Some(nl) = content.find('\n') {
+        content.drain(..=nl);
+    }
+    Some(CrashBundleFile {
+        path: path.display().to_string(),
+        size_bytes: size,
+        truncated,
+        content,
+    })
+}
+
+/// Soft `RLIMIT_CORE` of the runner (inherited by harness processes).
+fn core_limit() -> Option<u64> {
+    let mut limit = libc::rlimit {
+        rlim_cur: 0,
+        rlim_max: 0,
+    };
+    // SAFETY: getrlimit only writes into the provided struct.
+    if unsafe { libc::getrlimit(libc::RLIMIT_CORE, &mut limit) } != 0 {
+        return Some(0);
+    }
+    if limit.rlim_cur == libc::RLIM_INFINITY {
+        None
+    } else {
+        Some(limit.rlim_cur)
+    }
+}
+
+/// Locate a core dump for `pid`, either through systemd-coredump or as a
+/// core file written according to `core_pattern`.
+async fn find_core_dump(pid: u32, cwd: &Path, core_pattern: Option<&str>) -> Option<CoreDumpInfo> {
+    let pattern = core_pattern.unwrap_or("core");
+    if pattern.starts_with('|') {
+        if !pattern.contains("systemd-coredump") {
+            return None;
+        }
+        return coredumpctl_info(pid).await;
+    }
+
+    for path in core_file_candidates(pid, cwd, pattern) {
+        let Ok(meta) = tokio::fs::metadata(&path).await else {
+            continue;
+        };
+        let fresh = meta
+            .modified()
+            .ok()
+            .and_then(|m| SystemTime::now().duration_since(m).ok())
+            .is_some_and(|age| age <= CORE_FILE_MAX_AGE);
+        if !meta.is_file() || !fresh {
+            continue;
+        }
+        let data_base64 = if meta.len() <= MAX_INLINE_CORE_BYTES {
+            tokio::fs::read(&path)
+                .await
+                .ok()
+                .map(|data| base64::engine::general_purpose::STANDARD.encode(data))
+        } else {
+            None
+        };
+        return Some(CoreDumpInfo {
+            source: "file".to_string(),
+            path: Some(path.display().to_string()),
+            size_bytes: Some(meta.len()),
+            data_base64,
+            details: None,
+        });
+    }
+    None
+}
+
+/// Core file paths `pattern` can produce for `pid`. Only `%p` and `%%` are
+/// expanded; patterns with other specifiers fall back to the kernel defaults.
+fn core_file_candidates(pid: u32, cwd: &Path, pattern: &str) -> Vec<PathBuf> {
+    let mut candidates = Vec::new();
+    let expanded = pattern.replace("%%", "\0").replace("%p", &pid.to_string());
+    if !expanded.contains('%') {
+        let expanded = expanded.replace('\0', "%");
+        let path = Path::new(&expanded);
+        candidates.push(if path.is_absolute() {
+            path.to_path_buf()
+        } else {
+            cwd.join(path)
+        });
+    }
+    for default in [format!("core.{pid}"), "core".to_string()] {
+        let path = cwd.join(default);
+        if !candidates.contains(&path) {
+            candidates.push(path);
+        }
+    }
+    candidates
+}
+
+async fn coredumpctl_info(pid: u32) -> Option<CoreDumpInfo> {
+    // systemd-coredump processes the dump asynchronously after the crash.
+    tokio::time::sleep(Duration::from_secs(2)).await;
+    let output = tokio::time::timeout(
+        Duration::from_secs(10),
+        tokio::process::Command::new("coredumpctl")
+            .args(["--no-pager", "--quiet", "info", &pid.to_string()])
+            .output(),
+    )
+    .await
+    .ok()?
+    .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    let mut details = String::from_utf8_lossy(&output.stdout).into_owned();
+    if details.len() > MAX_COREDUMP_DETAILS_BYTES {
+        let mut cut = MAX_COREDUMP_DETAILS_BYTES;
+        while !details.is_char_boundary(cut) {
+            cut -= 1;
+        }
+        details.truncate(cut);
+    }
+    let path = details.lines().find_map(|line| {
+        line.trim()
+            .strip_prefix("Storage:")
+            .map(|s| s.trim().trim_end_matches(" (present)").to_string())
+    });
+    Some(CoreDumpInfo {
+        source: "systemd-coredump".to_string(),
+        path,
+        size_bytes: None,
+        data_base64: None,
+        details: Some(details),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn redacts_secret_keys() {
+        let env = redact_env([
+            ("ANTHROPIC_API_KEY".to_string(), "sk-ant".to_string()),
+            ("GITHUB_TOKEN".to_string(), "ghp".to_string()),
+            ("db_password".to_string(), "hunter2".to_string()),
+            ("PATH".to_string(), "/usr/bin".to_string()),
+            ("OQTO_SESSION_ID".to_string(), "ses_1".to_string()),
+        ]);
+        assert_eq!(env["ANTHROPIC_API_KEY"], REDACTED);
+        assert_eq!(env["GITHUB_TOKEN"], REDACTED);
+        assert_eq!(env["db_password"], REDACTED);
+        assert_eq!(env["PATH"], "/usr/bin");
+        assert_eq!(env["OQTO_SESSION_ID"], "ses_1");
+    }
+
+    #[test]
+    fn core_file_candidates_expand_pid() {
+        let cwd = Path::new("/work");
+        assert_eq!(
+            core_file_candidates(42, cwd, "/var/crash/core.%p"),
+            vec![
+                PathBuf::from("/var/crash/core.42"),
+                PathBuf::from("/work/core.42"),
+                PathBuf::from("/work/core"),
+            ]
+        );
+        assert_eq!(
+            core_file_candidates(42, cwd, "core"),
+            vec![PathBuf::from("/work/core"), PathBuf::from("/work/core.42")]
+        );
+        // Unsupported specifiers only leave the defaults.
+        assert_eq!(core_file_candidates(42, cwd, "core.%e.%t").len(), 2);
+    }
+
+    #[tokio::test]
+    async fn collect_list_and_get_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = CrashBundleStore::new(dir.path().join("bundles"), 2);
+        let session_file = dir.path().join("session.jsonl");
+        std::fs::write(&session_file, "{\"type\":\"session\"}\n").unwrap();
+        let ctx = CrashContext {
+            harness: "pi".to_string(),
+            binary: PathBuf::from("/usr/local/bin/pi"),
+            cwd: dir.path().to_path_buf(),
+            sandboxed: false,
+            pid: None,
+            env: redact_env([("OPENAI_API_KEY".to_string(), "sk".to_string())]),
+        };
+
+        let mut ids = Vec::new();
+        for sid in ["ses_a", "ses_b", "ses_c"] {
+            let summary = store
+                .collect(
+                    sid,
+                    Some("pi-native"),
+                    ExitStatus::from_raw(1 << 8),
+                    vec!["boom".to_string()],
+                    Some(&session_file),
+                    &ctx,
+                )
+                .await
+                .unwrap();
+            assert_eq!(summary.exit_code, Some(1));
+            assert_eq!(summary.last_stderr.as_deref(), Some("boom"));
+            ids.push(summary.id);
+            // Distinct, ordered timestamps.
+            tokio::time::sleep(Duration::from_millis(5)).await;
+        }
+
+        // Oldest bundle was pruned.
+        let all = store.list(None).await.unwrap();
+        assert_eq!(all.len(), 2);
+        assert_eq!(all[0].session_id, "ses_c");
+        assert!(store.get(&ids[0]).await.unwrap().is_none());
+
+        assert_eq!(store.list(Some("ses_b")).await.unwrap().len(), 1);
+        assert_eq!(store.list(Some("pi-native")).await.unwrap().len(), 2);
+
+        let bundle = store.get(&ids[2]).await.unwrap().unwrap();
+        assert_eq!(bundle.stderr, vec!["boom".to_string()]);
+        assert_eq!(
+            bundle.session_file.unwrap().content,
+            "{\"type\":\"session\"}\n"
+        );
+        assert_eq!(bundle.environment.vars["OPENAI_API_KEY"], REDACTED);
+        assert!(store.get("../etc").await.is_err());
+    }
+}
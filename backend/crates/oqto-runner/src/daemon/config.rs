@@ -7,6 +7,13 @@ use std::{
     time::Duration,
 };
 
+use crate::artifacts::ArtifactConfig;
+use crate::file_history::FileHistoryConfig;
+use crate::output_lint::OutputLintConfig;
+use crate::tool_approval::ToolApprovalConfig;
+use crate::tool_output::ToolOutputConfig;
+use crate::tool_rate_limit::ToolRateLimitConfig;
+
 #[derive(Debug, Clone, Default)]
 pub struct RunnerUserConfig {
     pub fileserver_binary: String,
@@ -18,6 +25,14 @@ pub struct RunnerUserConfig {
     pub memories_dir: PathBuf,
     pub single_user: bool,
     pub linux_users_enabled: bool,
+    pub tool_rate_limits: ToolRateLimitConfig,
+    pub tool_approvals: ToolApprovalConfig,
+    pub tool_output: ToolOutputConfig,
+    pub output_lint: OutputLintConfig,
+    pub artifacts: ArtifactConfig,
+    pub file_history: FileHistoryConfig,
+    /// Directory of harness manifests (`*.toml`).
+    pub harness_dir: PathBuf,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
@@ -76,6 +91,13 @@ struct RunnerSection {
     runner_id: Option<String>,
     pi_sessions_dir: Option<String>,
     memories_dir: Option<String>,
+    tool_rate_limits: ToolRateLimitConfig,
+    tool_approvals: ToolApprovalConfig,
+    tool_output: ToolOutputConfig,
+    output_lint: OutputLintConfig,
+    artifacts: ArtifactConfig,
+    file_history: FileHistoryConfig,
+    harness_dir: Option<String>,
 }
 
 impl RunnerUserConfig {
@@ -94,6 +116,10 @@ impl RunnerUserConfig {
     }
 
     pub fn load_from_path(path: PathBuf) -> Self {
+        let default_harness_dir = path
+            .parent()
+            .map(|dir| dir.join("harnesses"))
+            .unwrap_or_else(|| PathBuf::from("harnesses"));
         let config_file: ConfigFile = if path.exists() {
             match std::fs::read_to_string(&path) {
                 Ok(contents) => match toml::from_str(&contents) {
@@ -156,6 +182,17 @@ impl RunnerUserConfig {
                 .unwrap_or_else(|| data_dir.join("mmry")),
             single_user: config_file.local.single_user,
             linux_users_enabled: config_file.local.linux_users.enabled,
+            tool_rate_limits: config_file.runner.tool_rate_limits,
+            tool_approvals: config_file.runner.tool_approvals,
+            tool_output: config_file.runner.tool_output,
+            output_lint: config_file.runner.output_lint,
+            artifacts: config_file.runner.artifacts,
+            file_history: config_file.runner.file_history,
+            harness_dir: config_file
+                .runner
+                .harness_dir
+                .map(|p| Self::expand_path(&p, &home))
+                .unwrap_or(default_harness_dir),
         }
     }
 
@@ -0,0 +1,311 @@
+//! Opt-in workspace encryption at rest via gocryptfs.
+//!
+//! An encrypted workspace keeps its ciphertext in a hidden sibling directory
+//! (`<parent>/.<name>.oqto-crypt`) and is FUSE-mounted over the workspace path
+//! only while unlocked. The runner runs as the workspace owner, so the
+//! plaintext view is never visible to other users on the host, and nothing
+//! but ciphertext remains on disk once the workspace is locked.
+
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+
+use anyhow::{Context, Result, bail};
+use tokio::io::AsyncWriteExt;
+use tokio::process::Command;
+
+use crate::protocol::Passphrase;
+
+/// Suffix of the hidden ciphertext directory next to a workspace.
+const CIPHER_DIR_SUFFIX: &str = ".oqto-crypt";
+
+/// Suffix of the temporary directory used while migrating plaintext into a
+/// freshly enabled workspace.
+const MIGRATION_DIR_SUFFIX: &str = ".oqto-plain-migrate";
+
+/// gocryptfs exit code for a wrong password.
+const GOCRYPTFS_EXIT_PASSWORD_INCORRECT: i32 = 12;
+
+/// Errors callers need to tell apart from generic failures.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EncryptionError {
+    WrongPassphrase,
+    Unavailable,
+}
+
+impl std::fmt::Display for EncryptionError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::WrongPassphrase => f.write_str("incorrect passphrase"),
+            Self::Unavailable => f.write_str("gocryptfs is not installed on this host"),
+        }
+    }
+}
+
+impl std::error::Error for EncryptionError {}
+
+/// Ciphertext directory for `workspace`.
+pub fn cipher_dir(workspace: &Path) -> Option<PathBuf> {
+    let name = workspace.file_name()?.to_string_lossy();
+    Some(
+        workspace
+            .parent()?
+            .join(format!(".{name}{CIPHER_DIR_SUFFIX}")),
+    )
+}
+
+/// Whether encryption has been enabled for `workspace`.
+pub fn is_enabled(workspace: &Path) -> bool {
+    cipher_dir(workspace).is_some_and(|dir| dir.join("gocryptfs.conf").is_file())
+}
+
+/// Whether the decrypted view of `workspace` is currently mounted.
+pub fn is_mounted(workspace: &Path) -> bool {
+    std::fs::read_to_string("/proc/self/mounts")
+        .map(|mounts| mount_table_contains(&mounts, workspace))
+        .unwrap_or(false)
+}
+
+/// Nearest encrypted workspace containing `path` (including `path` itself).
+pub fn encrypted_root(path: &Path) -> Option<PathBuf> {
+    path.ancestors()
+        .find(|candidate| is_enabled(candidate))
+        .map(Path::to_path_buf)
+}
+
+/// Turn on encryption for `workspace`, leaving it unlocked.
+///
+/// Existing files are moved aside, the empty workspace is mounted, and the
+/// files are copied back through the mount so they end up encrypted. The
+/// plaintext originals are removed only after the copy succeeded.
+pub async fn enable(workspace: &Path, passphrase: &Passphrase) -> Result<()> {
+    let cipher = cipher_dir(workspace).context("workspace path has no parent directory")?;
+    if is_enabled(workspace) {
+        bail!("encryption is already enabled for {}", workspace.display());
+    }
+    ensure_available().await?;
+
+    create_private_dir(&cipher)?;
+    let mut init = Command::new("gocryptfs");
+    init.arg("-init").arg("-q").arg(&cipher);
+    if let Err(e) = run_with_passphrase(init, passphrase).await {
+        let _ = std::fs::remove_dir_all(&cipher);
+        return Err(e).context("initializing gocryptfs");
+    }
+
+    let staging = sibling(workspace, MIGRATION_DIR_SUFFIX)?;
+    let has_contents = workspace.is_dir() && std::fs::read_dir(workspace)?.next().is_some();
+    if has_contents {
+        std::fs::rename(workspace, &staging)
+            .with_context(|| format!("moving {} aside", workspace.display()))?;
+    }
+    create_private_dir(workspace)?;
+
+    if let Err(e) = mount(workspace, passphrase).await {
+        restore_staging(workspace, &staging, has_contents);
+        let _ = std::fs::remove_dir_all(&cipher);
+        return Err(e);
+    }
+
+    if has_contents {
+        let status = Command::new("cp")
+            .arg("-a")
+            .arg(staging.join("."))
+            .arg(workspace)
+            .status()
+            .await
+            .context("copying workspace files into the encrypted mount")?;
+        if !status.success() {
+            let _ = unmount(workspace).await;
+            restore_staging(workspace, &staging, true);
+            let _ = std::fs::remove_dir_all(&cipher);
+            bail!("copying workspace files into the encrypted mount failed ({status})");
+        }
+        tokio::fs::remove_dir_all(&staging)
+            .await
+            .with_context(|| format!("removing plaintext copy {}", staging.display()))?;
+    }
+    Ok(())
+}
+
+/// Mount the decrypted view of an encrypted workspace.
+pub async fn mount(workspace: &Path, passphrase: &Passphrase) -> Result<()> {
+    let cipher = cipher_dir(workspace).context("workspace path has no parent directory")?;
+    ensure_available().await?;
+    std::fs::create_dir_all(workspace)
+        .with_context(|| format!("creating mount point {}", workspace.display()))?;
+
+    let mut cmd = Command::new("gocryptfs");
+    cmd.arg("-q").arg(&cipher).arg(workspace);
+    run_with_passphrase(cmd, passphrase).await
+}
+
+/// Unmount the decrypted view, leaving only ciphertext on disk.
+pub async fn unmount(workspace: &Path) -> Result<()> {
+    let mut last_error = None;
+    for binary in ["fusermount3", "fusermount"] {
+        match Command::new(binary)
+            .arg("-u")
+            .arg(workspace)
+            .stdin(Stdio::null())
+            .output()
+            .await
+        {
+            Ok(output) if output.status.success() => return Ok(()),
+            Ok(output) => {
+                last_error = Some(anyhow::anyhow!(
+                    "{binary} -u failed: {}",
+                    String::from_utf8_lossy(&output.stderr).trim()
+                ));
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => continue,
+            Err(e) => last_error = Some(e.into()),
+        }
+    }
+    Err(last_error.unwrap_or_else(|| anyhow::anyhow!("fusermount is not installed")))
+}
+
+async fn ensure_available() -> Result<()> {
+    match Command::new("gocryptfs")
+        .arg("-version")
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .await
+    {
+        Ok(_) => Ok(()),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            Err(EncryptionError::Unavailable.into())
+        }
+        Err(e) => Err(e).context("running gocryptfs"),
+    }
+}
+
+/// gocryptfs reads the password from stdin when it is not a terminal.
+async fn run_with_passphrase(mut cmd: Command, passphrase: &Passphrase) -> Result<()> {
+    let mut child = cmd
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .spawn()
+        .context("spawning gocryptfs")?;
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin.write_all(passphrase.expose().as_bytes()).await?;
+        stdin.write_all(b"\n").await?;
+    }
+    let output = child
+        .wait_with_output()
+        .await
+        .context("waiting for gocryptfs")?;
+    if output.status.success() {
+        return Ok(());
+    }
+    if output.status.code() == Some(GOCRYPTFS_EXIT_PASSWORD_INCORRECT) {
+        return Err(EncryptionError::WrongPassphrase.into());
+    }
+    bail!(
+        "gocryptfs failed ({}): {}",
+        output.status,
+        String::from_utf8_lossy(&output.stderr).trim()
+    )
+}
+
+fn create_private_dir(path: &Path) -> Result<()> {
+    use std::os::unix::fs::DirBuilderExt;
+    std::fs::DirBuilder::new()
+        .recursive(true)
+        .mode(0o700)
+        .create(path)
+        .with_context(|| format!("creating {}", path.display()))
+}
+
+fn restore_staging(workspace: &Path, staging: &Path, moved: bool) {
+    if moved {
+        let _ = std::fs::remove_dir(workspace);
+        let _ = std::fs::rename(staging, workspace);
+    }
+}
+
+fn sibling(path: &Path, suffix: &str) -> Result<PathBuf> {
+    let name = path
+        .file_name()
+        .context("workspace path has no file name")?
+        .to_string_lossy();
+    Ok(path.with_file_name(format!(".{name}{suffix}")))
+}
+
+/// `/proc/mounts` escapes spaces, tabs, newlines and backslashes as octal.
+fn mount_table_contains(mounts: &str, mount_point: &Path) -> bool {
+    let target = mount_point.to_string_lossy();
+    let target = target.trim_end_matches('/');
+    mounts.lines().any(|line| {
+        line.split_whitespace()
+            .nth(1)
+            .is_some_and(|field| unescape_mount_field(field) == target)
+    })
+}
+
+fn unescape_mount_field(field: &str) -> String {
+    let mut out = String::with_capacity(field.len());
+    let mut rest = field;
+    while let Some(idx) = rest.find('\\') {
+        out.push_str(&rest[..idx]);
+        let escaped = rest.get(idx + 1..idx + 4);
+        match escaped.and_then(|oct| u8::from_str_radix(oct, 8).ok()) {
+            Some(byte) => {
+                out.push(byte as char);
+                rest = &rest[idx + 4..];
+            }
+            None => {
+                out.push('\\');
+                rest = &rest[idx + 1..];
+            }
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cipher_dir_is_hidden_sibling() {
+        assert_eq!(
+            cipher_dir(Path::new("/home/alice/oqto/client-a")),
+            Some(PathBuf::from("/home/alice/oqto/.client-a.oqto-crypt"))
+        );
+        assert_eq!(cipher_dir(Path::new("/")), None);
+    }
+
+    #[test]
+    fn mount_table_matches_escaped_paths() {
+        let mounts = "proc /proc proc rw 0 0\n\
+            /home/alice/oqto/.my\\040project.oqto-crypt /home/alice/oqto/my\\040project fuse.gocryptfs rw,nosuid,nodev 0 0\n";
+        assert!(mount_table_contains(
+            mounts,
+            Path::new("/home/alice/oqto/my project")
+        ));
+        assert!(mount_table_contains(
+            mounts,
+            Path::new("/home/alice/oqto/my project/")
+        ));
+        assert!(!mount_table_contains(mounts, Path::new("/home/alice/oqto")));
+    }
+
+    #[test]
+    fn encrypted_root_finds_enabled_ancestor() {
+        let dir = tempfile::tempdir().unwrap();
+        let workspace = dir.path().join("secret");
+        let cipher = cipher_dir(&workspace).unwrap();
+        std::fs::create_dir_all(&cipher).unwrap();
+        std::fs::write(cipher.join("gocryptfs.conf"), "{}").unwrap();
+
+        assert_eq!(
+            encrypted_root(&workspace.join("src/lib")),
+            Some(workspace.clone())
+        );
+        assert_eq!(encrypted_root(&dir.path().join("public")), None);
+    }
+}
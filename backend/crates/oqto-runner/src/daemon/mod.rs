@@ -1,4 +1,5 @@
 pub mod bootstrap;
 pub mod config;
+pub mod encryption;
 pub mod server;
 pub mod state;
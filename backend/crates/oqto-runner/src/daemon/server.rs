@@ -3,24 +3,55 @@ use chrono::TimeZone;
 use log::{debug, error, info, warn};
 use sqlx::Row;
 use std::collections::HashMap;
+use std::net::SocketAddr;
 #[cfg(target_os = "linux")]
 use std::os::fd::AsRawFd;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::process::Stdio;
 use std::sync::Arc;
-use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
-use tokio::net::{UnixListener, UnixStream};
+use tokio::io::{
+    AsyncBufRead, AsyncBufReadExt, AsyncReadExt, AsyncWrite, AsyncWriteExt, BufReader,
+};
+use tokio::net::{TcpListener, TcpStream, UnixListener, UnixStream};
 use tokio::process::Command;
 use tokio::sync::{Mutex, RwLock, broadcast};
+use tokio_stream::wrappers::ReceiverStream;
 
+use crate::background::BackgroundProcess;
 use crate::daemon::config::RunnerUserConfig;
 use crate::daemon::state::{ManagedProcess, RunnerState, SessionState, StdoutBuffer, StdoutEvent};
+use crate::grpc::GrpcConn;
 use crate::pi_manager::PiSessionManager;
 use crate::protocol::*;
 use oqto_sandbox::SandboxConfig;
 
 mod handlers;
 
+/// How often unlocked encrypted workspaces are checked for remaining sessions.
+const WORKSPACE_LOCK_INTERVAL_SECS: u64 = 60;
+
+/// How long a TCP client has to send its authentication line.
+const TCP_AUTH_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(10);
+
+/// Longest authentication line accepted from a TCP client.
+const TCP_AUTH_MAX_LINE: u64 = 4096;
+
+/// TCP listener for remote backends (`--listen`, see [`crate::remote`]).
+#[derive(Debug, Clone)]
+pub struct TcpListen {
+    pub address: SocketAddr,
+    pub token: String,
+}
+
+/// gRPC listener for remote backends (`--grpc-listen`, see [`crate::grpc`]).
+#[derive(Debug, Clone)]
+pub struct GrpcListen {
+    pub address: SocketAddr,
+    pub token: String,
+    /// TLS, with client certificates when it names a client CA.
+    pub tls: Option<crate::grpc::ServerTls>,
+}
+
 /// Configuration for session service binaries.
 #[derive(Debug, Clone)]
 pub struct SessionBinaries {
@@ -40,6 +71,10 @@ pub struct Runner {
     user_config: RunnerUserConfig,
     /// Pi session manager (manages Pi agent processes).
     pi_manager: Arc<PiSessionManager>,
+    /// Optional TCP listener for remote backends.
+    tcp_listen: Option<TcpListen>,
+    /// Optional gRPC listener for remote backends.
+    grpc_listen: Option<GrpcListen>,
 }
 
 #[derive(Debug, serde::Deserialize)]
@@ -652,7 +687,128 @@ impl Runner {
             binaries,
             user_config,
             pi_manager,
+            tcp_listen: None,
+            grpc_listen: None,
+        }
+    }
+
+    /// Also accept authenticated TCP connections (remote runner host).
+    pub fn with_tcp_listen(mut self, listen: TcpListen) -> Self {
+        self.tcp_listen = Some(listen);
+        self
+    }
+
+    /// Also serve gRPC on a TCP port (remote runner host).
+    pub fn with_grpc_listen(mut self, listen: GrpcListen) -> Self {
+        self.grpc_listen = Some(listen);
+        self
+    }
+
+    /// A handle sharing this runner's state, for serving one connection.
+    fn connection_runner(&self) -> Runner {
+        Runner {
+            state: Arc::clone(&self.state),
+            shutdown_tx: self.shutdown_tx.clone(),
+            sandbox_config: self.sandbox_config.clone(),
+            binaries: self.binaries.clone(),
+            user_config: self.user_config.clone(),
+            pi_manager: Arc::clone(&self.pi_manager),
+            tcp_listen: None,
+            grpc_listen: None,
+        }
+    }
+
+    /// The gRPC service, answering each call on an in-process connection
+    /// served like a socket connection.
+    fn grpc_service(&self) -> crate::grpc::RunnerGrpcService {
+        let template = self.connection_runner();
+        crate::grpc::RunnerGrpcService::new(Arc::new(move || {
+            let (client, server) = crate::grpc::connection_pair();
+            let runner = template.connection_runner();
+            tokio::spawn(async move {
+                let (reader, writer) = tokio::io::split(server);
+                runner
+                    .handle_connection(BufReader::new(reader), writer)
+                    .await;
+            });
+            client
+        }))
+    }
+
+    /// Resolves when the runner shuts down.
+    fn shutdown_signal(&self) -> impl std::future::Future<Output = ()> + Send + 'static {
+        let mut shutdown_rx = self.shutdown_tx.subscribe();
+        async move {
+            let _ = shutdown_rx.recv().await;
+        }
+    }
+
+    /// Serve gRPC: connections the Unix socket hands over through the
+    /// returned sender, and the `--grpc-listen` port if configured.
+    async fn start_grpc(&self) -> Result<tokio::sync::mpsc::Sender<std::io::Result<GrpcConn>>> {
+        let (tx, rx) = tokio::sync::mpsc::channel(16);
+        let unix_server = tonic::transport::Server::builder()
+            .add_service(self.grpc_service())
+            .serve_with_incoming_shutdown(ReceiverStream::new(rx), self.shutdown_signal());
+        tokio::spawn(async move {
+            if let Err(e) = unix_server.await {
+                error!("gRPC server on the runner socket failed: {}", e);
+            }
+        });
+
+        if let Some(listen) = &self.grpc_listen {
+            let mut builder = tonic::transport::Server::builder();
+            if let Some(tls) = &listen.tls {
+                builder = builder
+                    .tls_config(tls.config()?)
+                    .context("configuring gRPC TLS")?;
+            }
+            let listener = TcpListener::bind(listen.address)
+                .await
+                .with_context(|| format!("binding to {}", listen.address))?;
+            info!(
+                "Runner serving gRPC on {}://{}",
+                if listen.tls.is_some() {
+                    "https"
+                } else {
+                    "http"
+                },
+                listen.address
+            );
+            let incoming = futures::stream::unfold(listener, |listener| async move {
+                let accepted = listener.accept().await.map(|(stream, _)| {
+                    let _ = stream.set_nodelay(true);
+                    stream
+                });
+                Some((accepted, listener))
+            });
+            let tcp_server = builder
+                .add_service(self.grpc_service().with_token(&listen.token))
+                .serve_with_incoming_shutdown(incoming, self.shutdown_signal());
+            tokio::spawn(async move {
+                if let Err(e) = tcp_server.await {
+                    error!("gRPC server failed: {}", e);
+                }
+            });
+        }
+        Ok(tx)
+    }
+
+    /// Handle a Unix socket connection. Connections that open with the
+    /// HTTP/2 preface go to the gRPC server, the rest speak JSON lines.
+    async fn handle_unix_connection(
+        &self,
+        stream: UnixStream,
+        grpc: tokio::sync::mpsc::Sender<std::io::Result<GrpcConn>>,
+    ) {
+        let (reader, writer) = stream.into_split();
+        let mut reader = BufReader::new(reader);
+        if matches!(reader.fill_buf().await, Ok(buf) if crate::grpc::is_http2_preface(buf)) {
+            debug!("Client speaks gRPC");
+            let _ = grpc.send(Ok(GrpcConn::new(reader, writer))).await;
+            return;
         }
+        self.handle_connection(reader, writer).await;
     }
 
     fn request_kind(req: &RunnerRequest) -> String {
@@ -679,6 +835,8 @@ impl Runner {
             // Scanning and repairing JSONL chat metadata can be expensive for
             // users with large session histories.
             RunnerRequest::RepairWorkspaceChatHistory(_) => std::time::Duration::from_secs(120),
+            // A full integrity check plus snapshot reads the whole database.
+            RunnerRequest::CheckHistoryIntegrity(_) => std::time::Duration::from_secs(300),
             _ => std::time::Duration::from_secs(10),
         }
     }
@@ -1444,6 +1602,55 @@ impl Runner {
         }
     }
 
+    /// Extract an uploaded archive into a new directory, as this runner's
+    /// user.
+    async fn extract_archive(&self, req: ExtractArchiveRequest) -> RunnerResponse {
+        use crate::workspace_archive::{ExtractError, ExtractLimits};
+        use base64::Engine;
+
+        let data = match base64::engine::general_purpose::STANDARD.decode(&req.content_base64) {
+            Ok(data) => data,
+            Err(e) => {
+                return error_response(
+                    ErrorCode::InvalidRequest,
+                    format!("Invalid base64 content: {}", e),
+                );
+            }
+        };
+        let limits = ExtractLimits {
+            max_bytes: req.max_bytes,
+            max_files: req.max_files,
+        };
+        let path = req.path.clone();
+        let result = tokio::task::spawn_blocking(move || {
+            crate::workspace_archive::extract(&data, req.format, &path, limits)
+        })
+        .await;
+        match result {
+            Ok(Ok(report)) => RunnerResponse::ArchiveExtracted(ArchiveExtractedResponse {
+                path: req.path,
+                files: report.files,
+                bytes: report.bytes,
+                skipped: report.skipped,
+                manifests: report.manifests,
+            }),
+            Ok(Err(e)) => {
+                let code = match &e {
+                    ExtractError::Exists(_) => ErrorCode::PathExists,
+                    ExtractError::Invalid(_) | ExtractError::TooLarge(_) => {
+                        ErrorCode::InvalidRequest
+                    }
+                    ExtractError::Io(e) if e.kind() == std::io::ErrorKind::PermissionDenied => {
+                        ErrorCode::PermissionDenied
+                    }
+                    ExtractError::Io(_) => ErrorCode::IoError,
+                };
+                error_response(code, e.to_string())
+            }
+            Err(e) => error_response(ErrorCode::Internal, format!("Extract task failed: {}", e)),
+        }
+    }
+
     // ========================================================================
     // Session Operations (user-plane)
     // ========================================================================
@@ -1517,6 +1724,18 @@ impl Runner {
             );
         }
 
+        let secrets_dir = match crate::session_secrets::write_files(&req.session_id, &req.secrets) {
+            Ok(dir) => dir,
+            Err(e) => {
+                crate::session_secrets::remove_files(&req.session_id);
+                return error_response(
+                    ErrorCode::IoError,
+                    format!("Failed to write secret files: {}", e),
+                );
+            }
+        };
+        let secret_env = crate::session_secrets::process_env(&req.secrets, secrets_dir.as_deref());
+
         // Generate unique process IDs for this session
         let fileserver_id = format!("{}-fileserver", req.session_id);
         let ttyd_id = format!("{}-ttyd", req.session_id);
@@ -1534,14 +1753,45 @@ impl Runner {
                 req.workspace_path.to_string_lossy().to_string(),
             ],
             cwd: req.workspace_path.clone(),
-            env: HashMap::new(),
+            // Backends from before capability tokens send no secret; they
+            // rely on the fileserver accepting every request.
+            env: HashMap::from([match &req.fileserver_token_secret {
+                Some(secret) => ("OQTO_FILES_TOKEN_SECRET".to_string(), secret.clone()),
+                None => (
+                    "OQTO_FILES_ALLOW_UNAUTHENTICATED".to_string(),
+                    "true".to_string(),
+                ),
+            }]),
             sandboxed: false,
         };
 
         if let RunnerResponse::Error(e) = self.spawn_process(fileserver_req, false).await {
+            crate::session_secrets::remove_files(&req.session_id);
             return RunnerResponse::Error(e);
         }
 
+        // The terminal picks up the workspace's shell environment through
+        // its own ZDOTDIR.
+        let mut ttyd_env = HashMap::new();
+        if let Some(shell) = &req.shell {
+            let base_path = std::env::var("PATH").ok();
+            ttyd_env = crate::shell_env::process_env(shell, base_path.as_deref());
+            let user_zdotdir = std::env::var_os("ZDOTDIR")
+                .or_else(|| std::env::var_os("HOME"))
+                .map(PathBuf::from)
+                .unwrap_or_else(|| PathBuf::from("/"));
+            match crate::shell_env::write_zdotdir(&req.session_id, shell, &user_zdotdir) {
+                Ok(dir) => {
+                    ttyd_env.insert("ZDOTDIR".to_string(), dir.display().to_string());
+                }
+                Err(e) => warn!(
+                    "Failed to write shell profile for session {}: {}",
+                    req.session_id, e
+                ),
+            }
+        }
+        ttyd_env.extend(secret_env.clone());
+
         // Spawn ttyd
         let ttyd_req = SpawnProcessRequest {
             id: ttyd_id.clone(),
@@ -1558,7 +1808,7 @@ impl Runner {
                 "-l".to_string(),
             ],
             cwd: req.workspace_path.clone(),
-            env: HashMap::new(),
+            env: ttyd_env,
             sandboxed: false,
         };
 
@@ -1570,6 +1820,7 @@ impl Runner {
                     force: false,
                 })
                 .await;
+            crate::session_secrets::remove_files(&req.session_id);
             return RunnerResponse::Error(e);
         }
 
@@ -1582,6 +1833,8 @@ impl Runner {
             fileserver_port: req.fileserver_port,
             ttyd_port: req.ttyd_port,
             agent: req.agent.clone(),
+            shell: req.shell.clone(),
+            secret_env,
             started_at: std::time::Instant::now(),
         };
 
@@ -1634,6 +1887,8 @@ impl Runner {
                 force: false,
             })
             .await;
+        crate::shell_env::remove_zdotdir(&req.session_id);
+        crate::session_secrets::remove_files(&req.session_id);
 
         info!("Session {} stopped", req.session_id);
 
@@ -1642,6 +1897,50 @@ impl Runner {
         })
     }
 
+    /// Sample the resource usage of a session's processes.
+    async fn get_session_resources(&self, req: GetSessionResourcesRequest) -> RunnerResponse {
+        let (workspace, mut roots) = {
+            let state = self.state.read().await;
+            let Some(session) = state.sessions.get(&req.session_id) else {
+                return error_response(
+                    ErrorCode::SessionNotFound,
+                    format!("Session {} not found", req.session_id),
+                );
+            };
+            let roots: Vec<u32> = state
+                .processes
+                .values()
+                .filter(|p| p.cwd.starts_with(&session.workspace_path))
+                .map(|p| p.pid)
+                .collect();
+            (session.workspace_path.clone(), roots)
+        };
+        roots.extend(self.pi_manager.process_ids_in(&workspace).await);
+
+        let usage =
+            match tokio::task::spawn_blocking(move || crate::resource_usage::tree_usage(&roots))
+                .await
+            {
+                Ok(usage) => usage,
+                Err(e) => {
+                    return error_response(
+                        ErrorCode::Internal,
+                        format!("Failed to read resource usage: {}", e),
+                    );
+                }
+            };
+        let gpu_memory_bytes = crate::resource_usage::gpu_memory(&usage.pids).await;
+
+        RunnerResponse::SessionResources(SessionResourcesResponse {
+            session_id: req.session_id,
+            sampled_at: chrono::Utc::now().timestamp_millis(),
+            cpu_time_us: usage.cpu_time_us,
+            memory_bytes: usage.memory_bytes,
+            processes: usage.pids.len() as u32,
+            gpu_memory_bytes,
+        })
+    }
+
     // ========================================================================
     // Main Chat Operations (user-plane)
     // ========================================================================
@@ -2639,153 +2938,1191 @@ impl Runner {
         })
     }
 
-    // Pi Session Management Operations
-    // ========================================================================
-
-    /// Create or resume a Pi session.
-    async fn pi_create_session(&self, req: PiCreateSessionRequest) -> RunnerResponse {
-        info!(
-            "pi_create_session: session_id={}, cwd={:?}",
-            req.session_id, req.config.cwd
-        );
+    /// Check the hstry database with SQLite's integrity pragmas and, when it is
+    /// healthy, snapshot it next to the database for later recovery.
+    async fn check_history_integrity(&self, req: CheckHistoryIntegrityRequest) -> RunnerResponse {
+        use oqto_history::integrity::{self, IntegrityCheck};
 
-        // Convert protocol config to pi_manager config
-        let pi_config = crate::pi_manager::PiSessionConfig {
-            cwd: req.config.cwd,
-            provider: req.config.provider,
-            model: req.config.model,
-            session_file: req.config.session_file,
-            continue_session: req.config.continue_session,
-            env: req.config.env,
+        let Some(db_path) = oqto_history::legacy_hstry::hstry_db_path() else {
+            return RunnerResponse::HistoryIntegrity(HistoryIntegrityResponse {
+                database: None,
+                problems: Vec::new(),
+                latest_good_backup: None,
+            });
+        };
+        let backup_dir = db_path
+            .parent()
+            .map(|dir| dir.join("backups"))
+            .unwrap_or_else(|| PathBuf::from("backups"));
+        let stem = db_path
+            .file_stem()
+            .map(|s| s.to_string_lossy().into_owned())
+            .unwrap_or_else(|| "hstry".to_string());
+
+        let problems = match oqto_history::legacy_hstry::open_hstry_pool(&db_path).await {
+            Ok(pool) => {
+                let check = if req.full {
+                    IntegrityCheck::Full
+                } else {
+                    IntegrityCheck::Quick
+                };
+                match integrity::run_check(&pool, check).await {
+                    Ok(problems) => {
+                        if problems.is_empty()
+                            && req.backup
+                            && let Err(e) =
+                                integrity::snapshot(&pool, &backup_dir, &stem, req.keep_backups)
+                                    .await
+                        {
+                            warn!("Failed to snapshot hstry DB: {:#}", e);
+                        }
+                        problems
+                    }
+                    Err(e) => {
+                        return error_response(
+                            ErrorCode::IoError,
+                            format!("Failed to check hstry DB: {e:#}"),
+                        );
+                    }
+                }
+            }
+            Err(e) => vec![format!("failed to open database: {e:#}")],
         };
 
-        match self
-            .pi_manager
-            .get_or_create_session(&req.session_id, pi_config)
-            .await
-        {
-            Ok(real_session_id) => RunnerResponse::PiSessionCreated(PiSessionCreatedResponse {
-                session_id: real_session_id,
-            }),
-            Err(e) => error_response(
-                ErrorCode::Internal,
-                format!("Failed to create Pi session: {}", e),
-            ),
+        if !problems.is_empty() {
+            error!(
+                "hstry DB {} failed integrity check: {:?}",
+                db_path.display(),
+                problems
+            );
         }
+
+        RunnerResponse::HistoryIntegrity(HistoryIntegrityResponse {
+            database: Some(db_path.display().to_string()),
+            latest_good_backup: if problems.is_empty() {
+                None
+            } else {
+                integrity::latest_good_snapshot(&backup_dir, &stem)
+                    .await
+                    .map(|p| p.display().to_string())
+            },
+            problems,
+        })
     }
 
-    /// Send a prompt to a Pi session.
-    async fn pi_prompt(&self, req: PiPromptRequest) -> RunnerResponse {
-        debug!(
-            "pi_prompt: session_id={}, message_len={}, client_id={:?}",
-            req.session_id,
-            req.message.len(),
-            req.client_id
-        );
+    // ========================================================================
+    // Harness Diagnostics
+    // ========================================================================
 
-        match self
-            .pi_manager
-            .prompt(&req.session_id, &req.message, req.client_id.clone())
-            .await
-        {
-            Ok(()) => RunnerResponse::PiCommandAck {
-                session_id: req.session_id,
-            },
+    /// List crash bundles collected after abnormal Pi exits.
+    async fn list_crash_bundles(&self, req: ListCrashBundlesRequest) -> RunnerResponse {
+        let Some(store) = self.pi_manager.crash_bundles() else {
+            return RunnerResponse::CrashBundleList(CrashBundleListResponse {
+                bundles: Vec::new(),
+            });
+        };
+        match store.list(req.session_id.as_deref()).await {
+            Ok(bundles) => RunnerResponse::CrashBundleList(CrashBundleListResponse { bundles }),
             Err(e) => error_response(
-                ErrorCode::PiSessionNotFound,
-                format!("Failed to send prompt: {}", e),
+                ErrorCode::IoError,
+                format!("Failed to list crash bundles: {e:#}"),
             ),
         }
     }
 
-    /// Send a steering message to interrupt a Pi session.
-    async fn pi_steer(&self, req: PiSteerRequest) -> RunnerResponse {
-        debug!(
-            "pi_steer: session_id={}, message_len={}, client_id={:?}",
-            req.session_id,
-            req.message.len(),
-            req.client_id,
-        );
-
-        match self
-            .pi_manager
-            .steer_with_client_id(&req.session_id, &req.message, req.client_id)
-            .await
-        {
-            Ok(()) => RunnerResponse::PiCommandAck {
-                session_id: req.session_id,
-            },
-            Err(e) => error_response(
-                ErrorCode::PiSessionNotFound,
-                format!("Failed to send steer: {}", e),
+    /// Load a complete crash bundle.
+    async fn get_crash_bundle(&self, req: GetCrashBundleRequest) -> RunnerResponse {
+        let Some(store) = self.pi_manager.crash_bundles() else {
+            return error_response(
+                ErrorCode::CrashBundleNotFound,
+                "Crash bundle collection is disabled",
+            );
+        };
+        match store.get(&req.bundle_id).await {
+            Ok(Some(bundle)) => RunnerResponse::CrashBundle(Box::new(bundle)),
+            Ok(None) => error_response(
+                ErrorCode::CrashBundleNotFound,
+                format!("Crash bundle {} not found", req.bundle_id),
             ),
+            Err(e) => error_response(ErrorCode::InvalidRequest, format!("{e:#}")),
         }
     }
 
-    /// Queue a follow-up message for a Pi session.
-    async fn pi_follow_up(&self, req: PiFollowUpRequest) -> RunnerResponse {
-        debug!(
-            "pi_follow_up: session_id={}, message_len={}, client_id={:?}",
-            req.session_id,
-            req.message.len(),
-            req.client_id,
-        );
+    // ========================================================================
+    // Agent Artifacts
+    // ========================================================================
 
-        match self
-            .pi_manager
-            .follow_up_with_client_id(&req.session_id, &req.message, req.client_id)
-            .await
-        {
-            Ok(()) => RunnerResponse::PiCommandAck {
-                session_id: req.session_id,
-            },
+    /// List images captured from a session's artifacts directory.
+    async fn list_artifacts(&self, req: ListArtifactsRequest) -> RunnerResponse {
+        let Some(store) = self.pi_manager.artifacts() else {
+            return RunnerResponse::ArtifactList(ArtifactListResponse {
+                artifacts: Vec::new(),
+            });
+        };
+        match store.list(&req.session_id).await {
+            Ok(artifacts) => RunnerResponse::ArtifactList(ArtifactListResponse { artifacts }),
             Err(e) => error_response(
-                ErrorCode::PiSessionNotFound,
-                format!("Failed to send follow_up: {}", e),
+                ErrorCode::IoError,
+                format!("Failed to list artifacts: {e:#}"),
             ),
         }
     }
 
-    /// Abort a Pi session's current operation.
-    async fn pi_abort(&self, req: PiAbortRequest) -> RunnerResponse {
-        debug!("pi_abort: session_id={}", req.session_id);
+    /// Load a captured artifact.
+    async fn get_artifact(&self, req: GetArtifactRequest) -> RunnerResponse {
+        use base64::Engine;
 
-        match self.pi_manager.abort(&req.session_id).await {
-            Ok(()) => RunnerResponse::PiCommandAck {
-                session_id: req.session_id,
-            },
-            Err(e) => error_response(
-                ErrorCode::PiSessionNotFound,
-                format!("Failed to abort: {}", e),
+        let Some(store) = self.pi_manager.artifacts() else {
+            return error_response(ErrorCode::ArtifactNotFound, "Artifact capture is disabled");
+        };
+        match store.get(&req.artifact_id, req.thumbnail).await {
+            Ok(Some(content)) => RunnerResponse::Artifact(Box::new(ArtifactResponse {
+                session_id: content.session_id,
+                artifact: content.artifact,
+                mime_type: content.mime_type,
+                data_base64: base64::engine::general_purpose::STANDARD.encode(&content.data),
+            })),
+            Ok(None) => error_response(
+                ErrorCode::ArtifactNotFound,
+                format!("Artifact {} not found", req.artifact_id),
             ),
+            Err(e) => error_response(ErrorCode::InvalidRequest, format!("{e:#}")),
         }
     }
 
-    /// Compact a Pi session's conversation.
-    async fn pi_compact(&self, req: PiCompactRequest) -> RunnerResponse {
-        debug!(
-            "pi_compact: session_id={}, has_instructions={}",
-            req.session_id,
-            req.instructions.is_some()
-        );
+    // ========================================================================
+    // Background Processes
+    // ========================================================================
 
-        match self
-            .pi_manager
-            .compact(&req.session_id, req.instructions.as_deref())
-            .await
+    /// Start a supervised background process for a Pi session.
+    async fn start_background_process(&self, req: StartBackgroundProcessRequest) -> RunnerResponse {
+        if req
+            .command
+            .first()
+            .is_none_or(|program| program.trim().is_empty())
         {
-            Ok(()) => RunnerResponse::PiCommandAck {
-                session_id: req.session_id,
-            },
-            Err(e) => error_response(
-                ErrorCode::PiSessionNotFound,
-                format!("Failed to compact: {}", e),
-            ),
+            return error_response(ErrorCode::InvalidRequest, "command must not be empty");
         }
-    }
+        let Some(session) = self.pi_manager.get_session_config(&req.session_id).await else {
+            return error_response(
+                ErrorCode::PiSessionNotFound,
+                format!("Pi session '{}' not found", req.session_id),
+            );
+        };
+        let cwd = match Self::background_cwd(&session.cwd, req.cwd.as_deref()) {
+            Ok(cwd) => cwd,
+            Err(resp) => return resp,
+        };
 
-    /// Unsubscribe from a Pi session's events.
-    /// Note: Actual unsubscription happens when the broadcast receiver is dropped.
+        let live = self
+            .state
+            .read()
+            .await
+            .background
+            .values()
+            .filter(|p| p.info.session_id == req.session_id && p.is_live())
+            .count();
+        if live >= crate::background::MAX_PER_SESSION {
+            return error_response(
+                ErrorCode::BackgroundProcessLimit,
+                format!(
+                    "Session already runs {} background processes; stop one first",
+                    crate::background::MAX_PER_SESSION
+                ),
+            );
+        }
+
+        let id = format!("bg-{}", &uuid::Uuid::new_v4().simple().to_string()[..12]);
+        let pid = match self
+            .spawn_background_run(&id, &req.command, &cwd, &req.env)
+            .await
+        {
+            Ok(pid) => pid,
+            Err(resp) => return resp,
+        };
+
+        let name = req
+            .name
+            .filter(|n| !n.trim().is_empty())
+            .unwrap_or_else(|| {
+                std::path::Path::new(&req.command[0])
+                    .file_name()
+                    .map(|n| n.to_string_lossy().into_owned())
+                    .unwrap_or_else(|| req.command[0].clone())
+            });
+        let info = BackgroundProcessInfo {
+            id: id.clone(),
+            session_id: req.session_id,
+            name,
+            command: req.command,
+            cwd,
+            restart: req.restart,
+            status: BackgroundProcessStatus::Running,
+            pid: Some(pid),
+            exit_code: None,
+            restarts: 0,
+            started_at: chrono::Utc::now().to_rfc3339(),
+            ended_at: None,
+        };
+        info!(
+            "Started background process '{}' ({}) for session {}: {:?}",
+            info.name, id, info.session_id, info.command
+        );
+        self.state.write().await.background.insert(
+            id,
+            BackgroundProcess {
+                info: info.clone(),
+                env: req.env,
+                restart_at: None,
+                previous_output: Vec::new(),
+            },
+        );
+        RunnerResponse::BackgroundProcess(info)
+    }
+
+    /// Working directory for a background process: the session's working
+    /// directory or a directory inside it.
+    fn background_cwd(
+        session_cwd: &std::path::Path,
+        requested: Option<&std::path::Path>,
+    ) -> Result<PathBuf, RunnerResponse> {
+        let root = session_cwd
+            .canonicalize()
+            .unwrap_or_else(|_| session_cwd.to_path_buf());
+        let Some(requested) = requested else {
+            return Ok(root);
+        };
+        let cwd = root.join(requested).canonicalize().map_err(|e| {
+            error_response(
+                ErrorCode::PathNotFound,
+                format!("{}: {}", requested.display(), e),
+            )
+        })?;
+        if !cwd.starts_with(&root) || !cwd.is_dir() {
+            return Err(error_response(
+                ErrorCode::PathNotAllowed,
+                format!(
+                    "{} is not a directory inside the session's working directory",
+                    requested.display()
+                ),
+            ));
+        }
+        Ok(cwd)
+    }
+
+    /// Spawn one run of a background process under its ID.
+    async fn spawn_background_run(
+        &self,
+        id: &str,
+        command: &[String],
+        cwd: &std::path::Path,
+        env: &HashMap<String, String>,
+    ) -> Result<u32, RunnerResponse> {
+        let (binary, args) = crate::background::spawn_command(command);
+        let req = SpawnProcessRequest {
+            id: id.to_string(),
+            binary,
+            args,
+            cwd: cwd.to_path_buf(),
+            env: env.clone(),
+            sandboxed: self.sandbox_config.is_some(),
+        };
+        match self.spawn_process(req, true).await {
+            RunnerResponse::ProcessSpawned(spawned) => Ok(spawned.pid),
+            other => Err(other),
+        }
+    }
+
+    async fn list_background_processes(
+        &self,
+        req: ListBackgroundProcessesRequest,
+    ) -> RunnerResponse {
+        let state = self.state.read().await;
+        let mut processes: Vec<BackgroundProcessInfo> = state
+            .background
+            .values()
+            .filter(|p| {
+                req.session_id
+                    .as_deref()
+                    .is_none_or(|sid| p.info.session_id == sid)
+            })
+            .map(|p| p.info.clone())
+            .collect();
+        processes.sort_by(|a, b| a.started_at.cmp(&b.started_at));
+        RunnerResponse::BackgroundProcessList(BackgroundProcessListResponse { processes })
+    }
+
+    async fn stop_background_process(&self, req: StopBackgroundProcessRequest) -> RunnerResponse {
+        let mut state = self.state.write().await;
+        match Self::stop_background_locked(&mut state, &req.id).await {
+            Some(info) => RunnerResponse::BackgroundProcess(info),
+            None => error_response(
+                ErrorCode::BackgroundProcessNotFound,
+                format!("Background process '{}' not found", req.id),
+            ),
+        }
+    }
+
+    async fn get_background_process_logs(
+        &self,
+        req: GetBackgroundProcessLogsRequest,
+    ) -> RunnerResponse {
+        let state = self.state.read().await;
+        let Some(process) = state.background.get(&req.id) else {
+            return error_response(
+                ErrorCode::BackgroundProcessNotFound,
+                format!("Background process '{}' not found", req.id),
+            );
+        };
+        let current = match state
+            .processes
+            .get(&req.id)
+            .and_then(|p| p.stdout_buffer.as_ref())
+        {
+            Some(buffer) => buffer.lock().await.lines.clone(),
+            None => Vec::new(),
+        };
+        let lines = req
+            .lines
+            .unwrap_or(crate::background::DEFAULT_LOG_LINES)
+            .clamp(1, 10_000);
+        RunnerResponse::BackgroundProcessLogs(BackgroundProcessLogsResponse {
+            id: req.id,
+            lines: process.tail(&current, lines),
+        })
+    }
+
+    /// Stop a background process, keeping its record and output.
+    async fn stop_background_locked(
+        state: &mut RunnerState,
+        id: &str,
+    ) -> Option<BackgroundProcessInfo> {
+        let output = Self::take_background_run(state, id).await;
+        let process = state.background.get_mut(id)?;
+        if process.is_live() {
+            process.previous_output.extend(output);
+            process.info.status = BackgroundProcessStatus::Stopped;
+            process.info.ended_at = Some(chrono::Utc::now().to_rfc3339());
+            process.restart_at = None;
+            info!(
+                "Stopped background process '{}' ({})",
+                process.info.name, id
+            );
+        }
+        Some(process.info.clone())
+    }
+
+    /// Kill a background process's current run, if any, and return its
+    /// output.
+    async fn take_background_run(state: &mut RunnerState, id: &str) -> Vec<String> {
+        let Some(mut run) = state.processes.remove(id) else {
+            return Vec::new();
+        };
+        if run.is_running()
+            && let Err(e) = run.child.start_kill()
+        {
+            warn!("Failed to kill background process '{}': {}", id, e);
+        }
+        match run.stdout_buffer {
+            Some(buffer) => buffer.lock().await.lines.clone(),
+            None => Vec::new(),
+        }
+    }
+
+    /// Stop and forget a session's background processes. Called when the
+    /// session is closed or deleted.
+    async fn stop_session_background_processes(&self, session_id: &str) {
+        let mut state = self.state.write().await;
+        let ids: Vec<String> = state
+            .background
+            .values()
+            .filter(|p| p.info.session_id == session_id)
+            .map(|p| p.info.id.clone())
+            .collect();
+        for id in ids {
+            Self::stop_background_locked(&mut state, &id).await;
+            state.background.remove(&id);
+        }
+    }
+
+    /// Record exited background processes and restart crashed ones whose
+    /// backoff has elapsed.
+    async fn supervise_background_processes(&self) {
+        let now = std::time::Instant::now();
+        let due: Vec<(String, Vec<String>, PathBuf, HashMap<String, String>)> = {
+            let mut state = self.state.write().await;
+            let running: Vec<String> = state
+                .background
+                .values()
+                .filter(|p| p.info.status == BackgroundProcessStatus::Running)
+                .map(|p| p.info.id.clone())
+                .collect();
+            for id in running {
+                let exited = match state.processes.get_mut(&id) {
+                    Some(run) => {
+                        if run.is_running() {
+                            continue;
+                        }
+                        run.exit_code()
+                    }
+                    // The run is gone (e.g. killed directly); treat it as a crash.
+                    None => None,
+                };
+                let output = Self::take_background_run(&mut state, &id).await;
+                let Some(process) = state.background.get_mut(&id) else {
+                    continue;
+                };
+                if process.record_exit(exited, &output, now) {
+                    warn!(
+                        "Background process '{}' ({}) exited with {:?}; restarting",
+                        process.info.name, id, exited
+                    );
+                } else {
+                    info!(
+                        "Background process '{}' ({}) exited with {:?}",
+                        process.info.name, id, exited
+                    );
+                }
+            }
+            state
+                .background
+                .values()
+                .filter(|p| p.restart_due(now))
+                .map(|p| {
+                    (
+                        p.info.id.clone(),
+                        p.info.command.clone(),
+                        p.info.cwd.clone(),
+                        p.env.clone(),
+                    )
+                })
+                .collect()
+        };
+
+        for (id, command, cwd, env) in due {
+            let result = self.spawn_background_run(&id, &command, &cwd, &env).await;
+            let mut state = self.state.write().await;
+            let still_wanted = state
+                .background
+                .get(&id)
+                .is_some_and(|p| p.info.status == BackgroundProcessStatus::Restarting);
+            if !still_wanted {
+                // Stopped while restarting.
+                Self::take_background_run(&mut state, &id).await;
+                continue;
+            }
+            let Some(process) = state.background.get_mut(&id) else {
+                continue;
+            };
+            process.info.restarts += 1;
+            match result {
+                Ok(pid) => {
+                    process.info.status = BackgroundProcessStatus::Running;
+                    process.info.pid = Some(pid);
+                    process.info.ended_at = None;
+                    process.restart_at = None;
+                }
+                Err(resp) => {
+                    warn!("Failed to restart background process {}: {:?}", id, resp);
+                    process.record_exit(None, &[], now);
+                }
+            }
+        }
+    }
+
+    // ========================================================================
+    // Dependency Scans
+    // ========================================================================
+
+    /// Run vulnerability scanners over a workspace and return their reports.
+    async fn scan_dependencies(&self, req: ScanDependenciesRequest) -> RunnerResponse {
+        if req.scanners.is_empty() {
+            return error_response(ErrorCode::InvalidRequest, "no scanners requested");
+        }
+        let workspace = match tokio::fs::canonicalize(&req.workspace_path).await {
+            Ok(path) if path.is_dir() => path,
+            Ok(path) => {
+                return error_response(
+                    ErrorCode::NotADirectory,
+                    format!("{} is not a directory", path.display()),
+                );
+            }
+            Err(e) => {
+                return error_response(
+                    ErrorCode::PathNotFound,
+                    format!("{}: {}", req.workspace_path.display(), e),
+                );
+            }
+        };
+        let timeout = crate::dependency_scan::effective_timeout(req.timeout_secs);
+        let runs = crate::dependency_scan::scan(&workspace, &req.scanners, timeout).await;
+        RunnerResponse::DependencyScan(DependencyScanResponse {
+            workspace_path: workspace,
+            runs,
+        })
+    }
+
+    // ========================================================================
+    // File History
+    // ========================================================================
+
+    /// List the recorded versions of a workspace file.
+    async fn list_file_versions(&self, req: ListFileVersionsRequest) -> RunnerResponse {
+        let Some(store) = self.pi_manager.file_history() else {
+            return error_response(ErrorCode::FileVersionNotFound, "File history is disabled");
+        };
+        let workspace = match tokio::fs::canonicalize(&req.workspace_path).await {
+            Ok(path) => path,
+            Err(e) => {
+                return error_response(
+                    ErrorCode::PathNotFound,
+                    format!("{}: {}", req.workspace_path.display(), e),
+                );
+            }
+        };
+        match store.versions(&workspace, &req.path, req.limit).await {
+            Ok(versions) => RunnerResponse::FileVersions(FileVersionsResponse {
+                path: req.path,
+                versions,
+            }),
+            Err(e) => error_response(ErrorCode::InvalidRequest, format!("{e:#}")),
+        }
+    }
+
+    /// Read a workspace file at an earlier version.
+    async fn read_file_version(&self, req: ReadFileVersionRequest) -> RunnerResponse {
+        use base64::Engine;
+
+        let Some(store) = self.pi_manager.file_history() else {
+            return error_response(ErrorCode::FileVersionNotFound, "File history is disabled");
+        };
+        let workspace = match tokio::fs::canonicalize(&req.workspace_path).await {
+            Ok(path) => path,
+            Err(e) => {
+                return error_response(
+                    ErrorCode::PathNotFound,
+                    format!("{}: {}", req.workspace_path.display(), e),
+                );
+            }
+        };
+        match store.read(&workspace, &req.path, &req.selector).await {
+            Ok(Some((version, content))) => {
+                RunnerResponse::FileVersionContent(FileVersionContentResponse {
+                    path: req.path,
+                    version,
+                    size: content.len() as u64,
+                    content_base64: base64::engine::general_purpose::STANDARD.encode(&content),
+                })
+            }
+            Ok(None) => error_response(
+                ErrorCode::FileVersionNotFound,
+                format!("No such version of {}", req.path),
+            ),
+            Err(e) => error_response(ErrorCode::InvalidRequest, format!("{e:#}")),
+        }
+    }
+
+    // ========================================================================
+    // Git
+    // ========================================================================
+
+    /// Status of the workspace repository.
+    async fn git_status(&self, req: GitStatusRequest) -> RunnerResponse {
+        let workspace = match git_workspace(&req.workspace_path).await {
+            Ok(path) => path,
+            Err(resp) => return resp,
+        };
+        match crate::git::status(&workspace).await {
+            Ok(status) => RunnerResponse::GitStatus(status),
+            Err(e) => git_error(e),
+        }
+    }
+
+    /// Diff of uncommitted changes or of a commit.
+    async fn git_diff(&self, req: GitDiffRequest) -> RunnerResponse {
+        let workspace = match git_workspace(&req.workspace_path).await {
+            Ok(path) => path,
+            Err(resp) => return resp,
+        };
+        match crate::git::diff(
+            &workspace,
+            req.path.as_deref(),
+            req.staged,
+            req.commit.as_deref(),
+        )
+        .await
+        {
+            Ok(diff) => RunnerResponse::GitDiff(diff),
+            Err(e) => git_error(e),
+        }
+    }
+
+    /// Commit history of the workspace repository.
+    async fn git_log(&self, req: GitLogRequest) -> RunnerResponse {
+        let workspace = match git_workspace(&req.workspace_path).await {
+            Ok(path) => path,
+            Err(resp) => return resp,
+        };
+        match crate::git::log(
+            &workspace,
+            req.limit,
+            req.path.as_deref(),
+            req.rev.as_deref(),
+        )
+        .await
+        {
+            Ok(commits) => RunnerResponse::GitLog(GitLogResponse { commits }),
+            Err(e) => git_error(e),
+        }
+    }
+
+    /// Local branches of the workspace repository.
+    async fn git_branches(&self, req: GitBranchesRequest) -> RunnerResponse {
+        let workspace = match git_workspace(&req.workspace_path).await {
+            Ok(path) => path,
+            Err(resp) => return resp,
+        };
+        match crate::git::branches(&workspace).await {
+            Ok(branches) => RunnerResponse::GitBranches(branches),
+            Err(e) => git_error(e),
+        }
+    }
+
+    /// Check out (and optionally create) a branch.
+    async fn git_switch_branch(&self, req: GitSwitchBranchRequest) -> RunnerResponse {
+        let workspace = match git_workspace(&req.workspace_path).await {
+            Ok(path) => path,
+            Err(resp) => return resp,
+        };
+        if let Err(e) = crate::git::switch_branch(&workspace, &req.name, req.create).await {
+            return git_error(e);
+        }
+        match crate::git::branches(&workspace).await {
+            Ok(branches) => RunnerResponse::GitBranches(branches),
+            Err(e) => git_error(e),
+        }
+    }
+
+    /// Stage and commit changes.
+    async fn git_commit(&self, req: GitCommitRequest) -> RunnerResponse {
+        let workspace = match git_workspace(&req.workspace_path).await {
+            Ok(path) => path,
+            Err(resp) => return resp,
+        };
+        let author = req.author_name.as_deref().zip(req.author_email.as_deref());
+        match crate::git::commit(&workspace, &req.message, req.paths.as_deref(), author).await {
+            Ok(commit) => RunnerResponse::GitCommitted(commit),
+            Err(e) => git_error(e),
+        }
+    }
+
+    /// Push a branch to its remote.
+    async fn git_push(&self, req: GitPushRequest) -> RunnerResponse {
+        let workspace = match git_workspace(&req.workspace_path).await {
+            Ok(path) => path,
+            Err(resp) => return resp,
+        };
+        match crate::git::push(
+            &workspace,
+            req.remote.as_deref(),
+            req.branch.as_deref(),
+            req.set_upstream,
+            &req.credentials,
+        )
+        .await
+        {
+            Ok(pushed) => RunnerResponse::GitPushed(pushed),
+            Err(e) => git_error(e),
+        }
+    }
+
+    // ========================================================================
+    // Data Queries
+    // ========================================================================
+
+    /// Run a read-only SQL query over workspace files with DuckDB.
+    async fn query_data(&self, req: DataQueryRequest) -> RunnerResponse {
+        let workspace = match tokio::fs::canonicalize(&req.workspace_path).await {
+            Ok(path) if path.is_dir() => path,
+            Ok(path) => {
+                return error_response(
+                    ErrorCode::NotADirectory,
+                    format!("{} is not a directory", path.display()),
+                );
+            }
+            Err(e) => {
+                return error_response(
+                    ErrorCode::PathNotFound,
+                    format!("{}: {}", req.workspace_path.display(), e),
+                );
+            }
+        };
+        let max_rows = crate::data_query::effective_max_rows(req.max_rows);
+        let timeout = crate::data_query::effective_timeout(req.timeout_secs);
+        match crate::data_query::query(&workspace, &req.sql, max_rows, timeout).await {
+            Ok(result) => RunnerResponse::DataQuery(result),
+            Err(e) => {
+                let code = match e {
+                    crate::data_query::DataQueryError::Invalid(_) => ErrorCode::InvalidRequest,
+                    crate::data_query::DataQueryError::Unavailable(_) => {
+                        ErrorCode::DataQueryUnavailable
+                    }
+                    crate::data_query::DataQueryError::TimedOut(_) => ErrorCode::DataQueryTimeout,
+                    crate::data_query::DataQueryError::Failed(_) => ErrorCode::DataQueryFailed,
+                };
+                error_response(code, e.to_string())
+            }
+        }
+    }
+
+    // ========================================================================
+    // Template Tests
+    // ========================================================================
+
+    /// Run a template's smoke check in a project created from it.
+    async fn run_smoke_check(&self, req: SmokeCheckRequest) -> RunnerResponse {
+        let dir = match tokio::fs::canonicalize(&req.workspace_path).await {
+            Ok(path) if path.is_dir() => path,
+            Ok(path) => {
+                return error_response(
+                    ErrorCode::NotADirectory,
+                    format!("{} is not a directory", path.display()),
+                );
+            }
+            Err(e) => {
+                return error_response(
+                    ErrorCode::PathNotFound,
+                    format!("{}: {}", req.workspace_path.display(), e),
+                );
+            }
+        };
+        if req.command.trim().is_empty() {
+            return error_response(ErrorCode::InvalidRequest, "command is empty");
+        }
+        let timeout = crate::smoke_check::effective_timeout(req.timeout_secs);
+        match crate::smoke_check::run(&dir, &req.command, timeout).await {
+            Ok(result) => RunnerResponse::SmokeCheck(result),
+            Err(e) => error_response(
+                ErrorCode::SpawnFailed,
+                format!("failed to start smoke check: {e}"),
+            ),
+        }
+    }
+
+    // ========================================================================
+    // Workspace Encryption
+    // ========================================================================
+
+    /// Resolve and validate a workspace root for encryption operations.
+    ///
+    /// Only directories strictly inside the user's workspace root qualify;
+    /// encrypting the root itself would hide the runner's own state.
+    fn encryption_workspace_path(&self, path: &std::path::Path) -> Result<PathBuf, RunnerResponse> {
+        let root = self
+            .user_config
+            .workspace_dir
+            .canonicalize()
+            .unwrap_or_else(|_| self.user_config.workspace_dir.clone());
+        let workspace = path.canonicalize().map_err(|e| {
+            error_response(
+                ErrorCode::PathNotFound,
+                format!("Workspace {} not found: {}", path.display(), e),
+            )
+        })?;
+        if workspace == root || !workspace.starts_with(&root) {
+            return Err(error_response(
+                ErrorCode::PathNotAllowed,
+                format!("{} is not a workspace directory", path.display()),
+            ));
+        }
+        if !workspace.is_dir() {
+            return Err(error_response(
+                ErrorCode::NotADirectory,
+                format!("{} is not a directory", path.display()),
+            ));
+        }
+        Ok(workspace)
+    }
+
+    fn encryption_error(action: &str, err: anyhow::Error) -> RunnerResponse {
+        use crate::daemon::encryption::EncryptionError;
+
+        match err.downcast_ref::<EncryptionError>() {
+            Some(EncryptionError::WrongPassphrase) => {
+                error_response(ErrorCode::WrongPassphrase, "Incorrect passphrase")
+            }
+            Some(EncryptionError::Unavailable) => error_response(
+                ErrorCode::Internal,
+                "Workspace encryption requires gocryptfs on this host",
+            ),
+            None => error_response(
+                ErrorCode::IoError,
+                format!("Failed to {} workspace: {:#}", action, err),
+            ),
+        }
+    }
+
+    /// Query, enable, unlock or lock at-rest encryption for a workspace.
+    async fn workspace_encryption(&self, req: WorkspaceEncryptionRequest) -> RunnerResponse {
+        use crate::daemon::encryption;
+
+        let workspace = match self.encryption_workspace_path(&req.workspace_path) {
+            Ok(path) => path,
+            Err(resp) => return resp,
+        };
+
+        match req.action {
+            WorkspaceEncryptionAction::Status => {}
+            WorkspaceEncryptionAction::Enable { passphrase } => {
+                if encryption::is_enabled(&workspace) {
+                    return error_response(
+                        ErrorCode::InvalidRequest,
+                        "Encryption is already enabled for this workspace",
+                    );
+                }
+                if self.workspace_in_use(&workspace).await {
+                    return error_response(
+                        ErrorCode::SessionAlreadyRunning,
+                        "Stop all sessions in this workspace before enabling encryption",
+                    );
+                }
+                info!("Enabling encryption for workspace {}", workspace.display());
+                // Migrating existing files can outlive the request timeout; run it
+                // to completion so the workspace is never left half-migrated.
+                let target = workspace.clone();
+                let result =
+                    tokio::spawn(async move { encryption::enable(&target, &passphrase).await })
+                        .await
+                        .unwrap_or_else(|e| Err(anyhow::anyhow!("encryption task failed: {e}")));
+                if let Err(e) = result {
+                    return Self::encryption_error("encrypt", e);
+                }
+                self.state
+                    .write()
+                    .await
+                    .unlocked_workspaces
+                    .insert(workspace.clone());
+            }
+            WorkspaceEncryptionAction::Unlock { passphrase } => {
+                if !encryption::is_enabled(&workspace) {
+                    return error_response(
+                        ErrorCode::InvalidRequest,
+                        "Encryption is not enabled for this workspace",
+                    );
+                }
+                if !encryption::is_mounted(&workspace) {
+                    if let Err(e) = encryption::mount(&workspace, &passphrase).await {
+                        return Self::encryption_error("unlock", e);
+                    }
+                    info!("Unlocked encrypted workspace {}", workspace.display());
+                }
+                self.state
+                    .write()
+                    .await
+                    .unlocked_workspaces
+                    .insert(workspace.clone());
+            }
+            WorkspaceEncryptionAction::Lock => {
+                if self.workspace_in_use(&workspace).await {
+                    return error_response(
+                        ErrorCode::SessionAlreadyRunning,
+                        "Stop all sessions in this workspace before locking it",
+                    );
+                }
+                if encryption::is_mounted(&workspace) {
+                    if let Err(e) = encryption::unmount(&workspace).await {
+                        return Self::encryption_error("lock", e);
+                    }
+                    info!("Locked encrypted workspace {}", workspace.display());
+                }
+                self.state
+                    .write()
+                    .await
+                    .unlocked_workspaces
+                    .remove(&workspace);
+            }
+        }
+
+        RunnerResponse::WorkspaceEncryption(WorkspaceEncryptionResponse {
+            state: Self::encryption_state(&workspace),
+            workspace_path: workspace,
+        })
+    }
+
+    fn encryption_state(workspace: &std::path::Path) -> WorkspaceEncryptionState {
+        use crate::daemon::encryption;
+
+        if !encryption::is_enabled(workspace) {
+            WorkspaceEncryptionState::Disabled
+        } else if encryption::is_mounted(workspace) {
+            WorkspaceEncryptionState::Unlocked
+        } else {
+            WorkspaceEncryptionState::Locked
+        }
+    }
+
+    /// Whether any Pi session is running inside `workspace`.
+    async fn workspace_in_use(&self, workspace: &std::path::Path) -> bool {
+        self.pi_manager
+            .list_sessions()
+            .await
+            .iter()
+            .any(|session| session.cwd.starts_with(workspace))
+    }
+
+    /// Lock workspaces this runner unlocked once their last session is gone.
+    async fn lock_idle_workspaces(&self) {
+        use crate::daemon::encryption;
+
+        let unlocked: Vec<PathBuf> = {
+            let state = self.state.read().await;
+            if state.unlocked_workspaces.is_empty() {
+                return;
+            }
+            state.unlocked_workspaces.iter().cloned().collect()
+        };
+
+        for workspace in unlocked {
+            if self.workspace_in_use(&workspace).await {
+                continue;
+            }
+            if encryption::is_mounted(&workspace)
+                && let Err(e) = encryption::unmount(&workspace).await
+            {
+                // Keep tracking it so the next sweep retries.
+                warn!(
+                    "Failed to auto-lock workspace {}: {:#}",
+                    workspace.display(),
+                    e
+                );
+                continue;
+            }
+            info!("Auto-locked idle workspace {}", workspace.display());
+            self.state
+                .write()
+                .await
+                .unlocked_workspaces
+                .remove(&workspace);
+        }
+    }
+
+    // Pi Session Management Operations
+    // ========================================================================
+
+    /// Environment of the shell declared by the running session whose
+    /// workspace contains `cwd`, with the session's secrets, or nothing.
+    async fn workspace_shell_env(&self, cwd: &Path) -> HashMap<String, String> {
+        let state = self.state.read().await;
+        let Some(session) = state
+            .sessions
+            .values()
+            .filter(|session| cwd.starts_with(&session.workspace_path))
+            .max_by_key(|session| session.workspace_path.components().count())
+        else {
+            return HashMap::new();
+        };
+        let mut env = match &session.shell {
+            Some(shell) => {
+                let base_path = std::env::var("PATH").ok();
+                crate::shell_env::process_env(shell, base_path.as_deref())
+            }
+            None => HashMap::new(),
+        };
+        env.extend(session.secret_env.clone());
+        env
+    }
+
+    /// Create or resume a Pi session.
+    async fn pi_create_session(&self, req: PiCreateSessionRequest) -> RunnerResponse {
+        info!(
+            "pi_create_session: session_id={}, cwd={:?}",
+            req.session_id, req.config.cwd
+        );
+
+        if let Some(root) = crate::daemon::encryption::encrypted_root(&req.config.cwd) {
+            if !crate::daemon::encryption::is_mounted(&root) {
+                return error_response(
+                    ErrorCode::WorkspaceLocked,
+                    format!("Workspace {} is encrypted and locked", root.display()),
+                );
+            }
+            // Sessions started in a workspace unlocked elsewhere (or before a
+            // runner restart) still lock automatically when they end.
+            self.state.write().await.unlocked_workspaces.insert(root);
+        }
+
+        // Agents started in a session's workspace get its shell environment;
+        // variables set by the request win.
+        let mut env = self.workspace_shell_env(&req.config.cwd).await;
+        env.extend(req.config.env);
+
+        // Convert protocol config to pi_manager config
+        let pi_config = crate::pi_manager::PiSessionConfig {
+            cwd: req.config.cwd,
+            provider: req.config.provider,
+            model: req.config.model,
+            session_file: req.config.session_file,
+            continue_session: req.config.continue_session,
+            env,
+            harness: req.config.harness,
+            sandbox_profile: req.config.sandbox_profile,
+        };
+
+        match self
+            .pi_manager
+            .get_or_create_session(&req.session_id, pi_config)
+            .await
+        {
+            Ok(real_session_id) => RunnerResponse::PiSessionCreated(PiSessionCreatedResponse {
+                session_id: real_session_id,
+            }),
+            Err(e) => error_response(
+                ErrorCode::Internal,
+                format!("Failed to create Pi session: {}", e),
+            ),
+        }
+    }
+
+    /// Send a prompt to a Pi session.
+    async fn pi_prompt(&self, req: PiPromptRequest) -> RunnerResponse {
+        debug!(
+            "pi_prompt: session_id={}, message_len={}, client_id={:?}",
+            req.session_id,
+            req.message.len(),
+            req.client_id
+        );
+
+        match self
+            .pi_manager
+            .prompt(&req.session_id, &req.message, req.client_id.clone())
+            .await
+        {
+            Ok(()) => RunnerResponse::PiCommandAck {
+                session_id: req.session_id,
+            },
+            Err(e) => error_response(
+                ErrorCode::PiSessionNotFound,
+                format!("Failed to send prompt: {}", e),
+            ),
+        }
+    }
+
+    /// Send a steering message to interrupt a Pi session.
+    async fn pi_steer(&self, req: PiSteerRequest) -> RunnerResponse {
+        debug!(
+            "pi_steer: session_id={}, message_len={}, client_id={:?}",
+            req.session_id,
+            req.message.len(),
+            req.client_id,
+        );
+
+        match self
+            .pi_manager
+            .steer_with_client_id(&req.session_id, &req.message, req.client_id)
+            .await
+        {
+            Ok(()) => RunnerResponse::PiCommandAck {
+                session_id: req.session_id,
+            },
+            Err(e) => error_response(
+                ErrorCode::PiSessionNotFound,
+                format!("Failed to send steer: {}", e),
+            ),
+        }
+    }
+
+    /// Queue a follow-up message for a Pi session.
+    async fn pi_follow_up(&self, req: PiFollowUpRequest) -> RunnerResponse {
+        debug!(
+            "pi_follow_up: session_id={}, message_len={}, client_id={:?}",
+            req.session_id,
+            req.message.len(),
+            req.client_id,
+        );
+
+        match self
+            .pi_manager
+            .follow_up_with_client_id(&req.session_id, &req.message, req.client_id)
+            .await
+        {
+            Ok(()) => RunnerResponse::PiCommandAck {
+                session_id: req.session_id,
+            },
+            Err(e) => error_response(
+                ErrorCode::PiSessionNotFound,
+                format!("Failed to send follow_up: {}", e),
+            ),
+        }
+    }
+
+    /// Interject guidance into a Pi session.
+    async fn pi_interject(&self, req: PiInterjectRequest) -> RunnerResponse {
+        debug!(
+            "pi_interject: session_id={}, message_len={}, client_id={:?}",
+            req.session_id,
+            req.message.len(),
+            req.client_id,
+        );
+
+        match self
+            .pi_manager
+            .interject(&req.session_id, &req.message, req.client_id)
+            .await
+        {
+            Ok((interjection_id, delivery)) => {
+                RunnerResponse::PiInterjected(PiInterjectedResponse {
+                    session_id: req.session_id,
+                    interjection_id,
+                    delivery,
+                })
+            }
+            Err(e) => error_response(
+                ErrorCode::PiSessionNotFound,
+                format!("Failed to interject: {}", e),
+            ),
+        }
+    }
+
+    /// Abort a Pi session's current operation.
+    async fn pi_abort(&self, req: PiAbortRequest) -> RunnerResponse {
+        debug!("pi_abort: session_id={}", req.session_id);
+
+        match self.pi_manager.abort(&req.session_id).await {
+            Ok(()) => RunnerResponse::PiCommandAck {
+                session_id: req.session_id,
+            },
+            Err(e) => error_response(
+                ErrorCode::PiSessionNotFound,
+                format!("Failed to abort: {}", e),
+            ),
+        }
+    }
+
+    /// Compact a Pi session's conversation.
+    async fn pi_compact(&self, req: PiCompactRequest) -> RunnerResponse {
+        debug!(
+            "pi_compact: session_id={}, has_instructions={}",
+            req.session_id,
+            req.instructions.is_some()
+        );
+
+        match self
+            .pi_manager
+            .compact(&req.session_id, req.instructions.as_deref())
+            .await
+        {
+            Ok(()) => RunnerResponse::PiCommandAck {
+                session_id: req.session_id,
+            },
+            Err(e) => error_response(
+                ErrorCode::PiSessionNotFound,
+                format!("Failed to compact: {}", e),
+            ),
+        }
+    }
+
+    /// Unsubscribe from a Pi session's events.
+    /// Note: Actual unsubscription happens when the broadcast receiver is dropped.
     /// This is just an acknowledgment.
     async fn pi_unsubscribe(&self, req: PiUnsubscribeRequest) -> RunnerResponse {
         debug!("pi_unsubscribe: session_id={}", req.session_id);
@@ -2821,7 +4158,9 @@ impl Runner {
     async fn pi_close_session(&self, req: PiCloseSessionRequest) -> RunnerResponse {
         info!("pi_close_session: session_id={}", req.session_id);
 
-        match self.pi_manager.close_session(&req.session_id).await {
+        self.stop_session_background_processes(&req.session_id)
+            .await;
+        let resp = match self.pi_manager.close_session(&req.session_id).await {
             Ok(()) => RunnerResponse::PiSessionClosed {
                 session_id: req.session_id,
             },
@@ -2829,7 +4168,9 @@ impl Runner {
                 ErrorCode::PiSessionNotFound,
                 format!("Failed to close session: {}", e),
             ),
-        }
+        };
+        self.lock_idle_workspaces().await;
+        resp
     }
 
     /// Delete a Pi session: close the process, remove from oqto-log, and delete the JSONL file.
@@ -2844,7 +4185,10 @@ impl Runner {
         .await
         .unwrap_or_else(|| req.session_id.clone());
 
+        self.stop_session_background_processes(&req.session_id)
+            .await;
         let _ = self.pi_manager.close_session(&req.session_id).await;
+        self.lock_idle_workspaces().await;
 
         if let Err(e) = oqto_history::oqto_log::ops::delete_session(
             std::path::Path::new(&home),
@@ -3650,7 +4994,7 @@ impl Runner {
     /// Return runner-advertised capabilities for backend negotiation.
     async fn get_capabilities(&self) -> RunnerResponse {
         RunnerResponse::RunnerCapabilities(RunnerCapabilitiesResponse {
-            harnesses: vec!["pi".to_string()],
+            harnesses: self.pi_manager.harness_names(),
             features: RunnerFeatureFlags {
                 command_discovery: true,
                 model_discovery: true,
@@ -3777,6 +5121,41 @@ impl Runner {
         }
     }
 
+    /// List a session's tool calls waiting for approval.
+    async fn list_tool_approvals(&self, req: ListToolApprovalsRequest) -> RunnerResponse {
+        match self.pi_manager.list_tool_approvals(&req.session_id).await {
+            Ok(approvals) => RunnerResponse::ToolApprovalList(ToolApprovalListResponse {
+                session_id: req.session_id,
+                approvals,
+            }),
+            Err(e) => error_response(ErrorCode::PiSessionNotFound, e.to_string()),
+        }
+    }
+
+    /// Approve or deny a tool call waiting for approval.
+    async fn resolve_tool_approval(&self, req: ResolveToolApprovalRequest) -> RunnerResponse {
+        debug!(
+            "resolve_tool_approval: session_id={}, approval_id={}, approved={}",
+            req.session_id, req.approval_id, req.approved
+        );
+
+        match self
+            .pi_manager
+            .resolve_tool_approval(&req.session_id, &req.approval_id, req.approved, req.reason)
+            .await
+        {
+            Ok(Some(approval)) => RunnerResponse::ToolApprovalResolved(approval),
+            Ok(None) => error_response(
+                ErrorCode::ToolApprovalNotFound,
+                format!(
+                    "No pending tool approval '{}' in session '{}'",
+                    req.approval_id, req.session_id
+                ),
+            ),
+            Err(e) => error_response(ErrorCode::PiSessionNotFound, e.to_string()),
+        }
+    }
+
     /// Handle Pi subscription streaming.
     /// Subscribes to the PiSessionManager's per-subscriber channel and streams events.
     ///
@@ -3791,10 +5170,10 @@ impl Runner {
             .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e.to_string()))
     }
 
-    async fn handle_pi_subscribe(
+    async fn handle_pi_subscribe<W: AsyncWrite + Unpin>(
         &self,
         session_id: &str,
-        writer: &mut tokio::net::unix::OwnedWriteHalf,
+        writer: &mut W,
     ) -> Result<(), std::io::Error> {
         info!("handle_pi_subscribe: session_id={}", session_id);
 
@@ -3851,11 +5230,37 @@ impl Runner {
         Ok(())
     }
 
-    /// Handle a client connection.
-    async fn handle_connection(&self, stream: UnixStream) {
+    /// Handle a TCP client connection: authenticate it, then serve it like
+    /// a socket connection.
+    async fn handle_tcp_connection(&self, stream: TcpStream, addr: SocketAddr, token: &str) {
+        let _ = stream.set_nodelay(true);
         let (reader, mut writer) = stream.into_split();
         let mut reader = BufReader::new(reader);
         let mut line = String::new();
+        let read = tokio::time::timeout(
+            TCP_AUTH_TIMEOUT,
+            (&mut reader).take(TCP_AUTH_MAX_LINE).read_line(&mut line),
+        )
+        .await;
+        if !matches!(read, Ok(Ok(n)) if n > 0) || !crate::remote::check_auth_line(&line, token) {
+            warn!("Rejected unauthenticated TCP connection from {}", addr);
+            let resp = error_response(ErrorCode::PermissionDenied, "authentication failed");
+            if let Ok(line) = Self::serialize_response_line(&resp) {
+                let _ = writer.write_all(line.as_bytes()).await;
+            }
+            return;
+        }
+        debug!("New TCP client connection from {}", addr);
+        self.handle_connection(reader, writer).await;
+    }
+
+    /// Handle a client connection.
+    async fn handle_connection<R, W>(&self, mut reader: R, mut writer: W)
+    where
+        R: AsyncBufRead + Unpin,
+        W: AsyncWrite + Unpin,
+    {
+        let mut line = String::new();
 
         loop {
             line.clear();
@@ -4088,28 +5493,48 @@ impl Runner {
 
         info!("Runner listening on {:?}", socket_path);
 
+        let grpc_tx = self.start_grpc().await?;
+
+        let tcp_listener = match &self.tcp_listen {
+            Some(listen) => {
+                let listener = TcpListener::bind(listen.address)
+                    .await
+                    .with_context(|| format!("binding to {}", listen.address))?;
+                info!("Runner listening on tcp://{}", listen.address);
+                Some((listener, listen.token.clone()))
+            }
+            None => None,
+        };
+
         // Notify systemd that we're ready (Type=notify).
         // This unblocks `systemctl start` so callers know the socket is live.
         sd_notify_ready();
 
         let mut shutdown_rx = self.shutdown_tx.subscribe();
+        let mut background_interval = tokio::time::interval(crate::background::SUPERVISE_INTERVAL);
+        background_interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
+        // Pi sessions can also end through idle cleanup or by exiting, so
+        // unlocked workspaces are re-checked periodically as well.
+        let mut lock_interval =
+            tokio::time::interval(std::time::Duration::from_secs(WORKSPACE_LOCK_INTERVAL_SECS));
+        lock_interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
 
         loop {
             tokio::select! {
+                _ = lock_interval.tick() => {
+                    self.lock_idle_workspaces().await;
+                }
+                _ = background_interval.tick() => {
+                    self.supervise_background_processes().await;
+                }
                 result = listener.accept() => {
                     match result {
                         Ok((stream, _addr)) => {
                             debug!("New client connection");
-                            let runner = Runner {
-                                state: Arc::clone(&self.state),
-                                shutdown_tx: self.shutdown_tx.clone(),
-                                sandbox_config: self.sandbox_config.clone(),
-                                binaries: self.binaries.clone(),
-                                user_config: self.user_config.clone(),
-                                pi_manager: Arc::clone(&self.pi_manager),
-                            };
+                            let runner = self.connection_runner();
+                            let grpc_tx = grpc_tx.clone();
                             tokio::spawn(async move {
-                                runner.handle_connection(stream).await;
+                                runner.handle_unix_connection(stream, grpc_tx).await;
                             });
                         }
                         Err(e) => {
@@ -4117,6 +5542,19 @@ impl Runner {
                         }
                     }
                 }
+                result = accept_tcp(tcp_listener.as_ref()) => {
+                    match result {
+                        Ok((stream, addr, token)) => {
+                            let runner = self.connection_runner();
+                            tokio::spawn(async move {
+                                runner.handle_tcp_connection(stream, addr, &token).await;
+                            });
+                        }
+                        Err(e) => {
+                            error!("TCP accept error: {}", e);
+                        }
+                    }
+                }
                 _ = shutdown_rx.recv() => {
                     info!("Shutting down...");
                     break;
@@ -4133,6 +5571,19 @@ impl Runner {
             }
         }
 
+        // Never leave decrypted workspaces mounted behind a stopped runner.
+        for workspace in state.unlocked_workspaces.drain() {
+            if crate::daemon::encryption::is_mounted(&workspace)
+                && let Err(e) = crate::daemon::encryption::unmount(&workspace).await
+            {
+                warn!(
+                    "Failed to lock workspace {} on shutdown: {:#}",
+                    workspace.display(),
+                    e
+                );
+            }
+        }
+
         // Remove socket file
         let _ = tokio::fs::remove_file(socket_path).await;
 
@@ -4141,6 +5592,19 @@ impl Runner {
     }
 }
 
+/// Accept the next TCP connection, or wait forever without a TCP listener.
+async fn accept_tcp(
+    listener: Option<&(TcpListener, String)>,
+) -> std::io::Result<(TcpStream, SocketAddr, String)> {
+    match listener {
+        Some((listener, token)) => {
+            let (stream, addr) = listener.accept().await?;
+            Ok((stream, addr, token.clone()))
+        }
+        None => std::future::pending().await,
+    }
+}
+
 fn error_response(code: ErrorCode, message: impl Into<String>) -> RunnerResponse {
     RunnerResponse::Error(ErrorResponse {
         code,
@@ -4148,6 +5612,25 @@ fn error_response(code: ErrorCode, message: impl Into<String>) -> RunnerResponse
     })
 }
 
+/// Canonical workspace path of a git request.
+async fn git_workspace(path: &std::path::Path) -> std::result::Result<PathBuf, RunnerResponse> {
+    tokio::fs::canonicalize(path).await.map_err(|e| {
+        error_response(
+            ErrorCode::PathNotFound,
+            format!("{}: {}", path.display(), e),
+        )
+    })
+}
+
+fn git_error(e: anyhow::Error) -> RunnerResponse {
+    let code = if e.downcast_ref::<crate::git::NotARepository>().is_some() {
+        ErrorCode::NotAGitRepository
+    } else {
+        ErrorCode::GitFailed
+    };
+    error_response(code, format!("{e:#}"))
+}
+
 /// Notify systemd that the service is ready (sd_notify READY=1).
 /// No-op if $NOTIFY_SOCKET is not set (i.e., not running under systemd Type=notify).
 fn sd_notify_ready() {
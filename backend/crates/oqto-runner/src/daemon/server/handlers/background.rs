@@ -0,0 +1,14 @@
+use super::super::*;
+
+pub(crate) async fn handle_request(runner: &Runner, req: RunnerRequest) -> RunnerResponse {
+    match req {
+        RunnerRequest::StartBackgroundProcess(r) => runner.start_background_process(r).await,
+        RunnerRequest::ListBackgroundProcesses(r) => runner.list_background_processes(r).await,
+        RunnerRequest::StopBackgroundProcess(r) => runner.stop_background_process(r).await,
+        RunnerRequest::GetBackgroundProcessLogs(r) => runner.get_background_process_logs(r).await,
+        _ => error_response(
+            ErrorCode::InvalidRequest,
+            "Invalid background process request",
+        ),
+    }
+}
@@ -0,0 +1,8 @@
+use super::super::*;
+
+pub(crate) async fn handle_request(runner: &Runner, req: RunnerRequest) -> RunnerResponse {
+    match req {
+        RunnerRequest::QueryData(r) => runner.query_data(r).await,
+        _ => error_response(ErrorCode::InvalidRequest, "Invalid data query request"),
+    }
+}
@@ -0,0 +1,8 @@
+use super::super::*;
+
+pub(crate) async fn handle_request(runner: &Runner, req: RunnerRequest) -> RunnerResponse {
+    match req {
+        RunnerRequest::ScanDependencies(r) => runner.scan_dependencies(r).await,
+        _ => error_response(ErrorCode::InvalidRequest, "Invalid dependency scan request"),
+    }
+}
@@ -0,0 +1,11 @@
+use super::super::*;
+
+pub(crate) async fn handle_request(runner: &Runner, req: RunnerRequest) -> RunnerResponse {
+    match req {
+        RunnerRequest::ListCrashBundles(r) => runner.list_crash_bundles(r).await,
+        RunnerRequest::GetCrashBundle(r) => runner.get_crash_bundle(r).await,
+        RunnerRequest::ListArtifacts(r) => runner.list_artifacts(r).await,
+        RunnerRequest::GetArtifact(r) => runner.get_artifact(r).await,
+        _ => error_response(ErrorCode::InvalidRequest, "Invalid diagnostics request"),
+    }
+}
@@ -24,12 +24,51 @@ pub(crate) async fn handle_request(runner: &Runner, req: RunnerRequest) -> Runne
         | RunnerRequest::ListDirectory(_)
         | RunnerRequest::Stat(_)
         | RunnerRequest::DeletePath(_)
-        | RunnerRequest::CreateDirectory(_)) => super::files::handle_request(runner, req).await,
+        | RunnerRequest::CreateDirectory(_)
+        | RunnerRequest::ExtractArchive(_)) => super::files::handle_request(runner, req).await,
+
+        req @ RunnerRequest::WorkspaceEncryption(_) => {
+            super::encryption::handle_request(runner, req).await
+        }
+
+        req @ (RunnerRequest::ListCrashBundles(_)
+        | RunnerRequest::GetCrashBundle(_)
+        | RunnerRequest::ListArtifacts(_)
+        | RunnerRequest::GetArtifact(_)) => super::diagnostics::handle_request(runner, req).await,
+
+        req @ (RunnerRequest::StartBackgroundProcess(_)
+        | RunnerRequest::ListBackgroundProcesses(_)
+        | RunnerRequest::StopBackgroundProcess(_)
+        | RunnerRequest::GetBackgroundProcessLogs(_)) => {
+            super::background::handle_request(runner, req).await
+        }
+
+        req @ RunnerRequest::ScanDependencies(_) => {
+            super::dependency_scan::handle_request(runner, req).await
+        }
+
+        req @ (RunnerRequest::ListFileVersions(_) | RunnerRequest::ReadFileVersion(_)) => {
+            super::file_history::handle_request(runner, req).await
+        }
+
+        req @ (RunnerRequest::GitStatus(_)
+        | RunnerRequest::GitDiff(_)
+        | RunnerRequest::GitLog(_)
+        | RunnerRequest::GitBranches(_)
+        | RunnerRequest::GitSwitchBranch(_)
+        | RunnerRequest::GitCommit(_)
+        | RunnerRequest::GitPush(_)) => super::git::handle_request(runner, req).await,
+
+        req @ RunnerRequest::QueryData(_) => super::data_query::handle_request(runner, req).await,
+        req @ RunnerRequest::RunSmokeCheck(_) => {
+            super::smoke_check::handle_request(runner, req).await
+        }
 
         req @ (RunnerRequest::ListSessions
         | RunnerRequest::GetSession(_)
         | RunnerRequest::StartSession(_)
         | RunnerRequest::StopSession(_)
+        | RunnerRequest::GetSessionResources(_)
         | RunnerRequest::ListMainChatSessions
         | RunnerRequest::GetMainChatMessages(_)
         | RunnerRequest::GetWorkspaceChatMessages(_)
@@ -37,7 +76,8 @@ pub(crate) async fn handle_request(runner: &Runner, req: RunnerRequest) -> Runne
         | RunnerRequest::GetWorkspaceChatSession(_)
         | RunnerRequest::GetWorkspaceChatSessionMessages(_)
         | RunnerRequest::UpdateWorkspaceChatSession(_)
-        | RunnerRequest::RepairWorkspaceChatHistory(_)) => {
+        | RunnerRequest::RepairWorkspaceChatHistory(_)
+        | RunnerRequest::CheckHistoryIntegrity(_)) => {
             super::sessions::handle_request(runner, req).await
         }
 
@@ -61,6 +101,7 @@ pub(crate) async fn handle_request(runner: &Runner, req: RunnerRequest) -> Runne
         | RunnerRequest::PiPrompt(_)
         | RunnerRequest::PiSteer(_)
         | RunnerRequest::PiFollowUp(_)
+        | RunnerRequest::PiInterject(_)
         | RunnerRequest::PiAbort(_)
         | RunnerRequest::PiGetState(_)
         | RunnerRequest::PiGetMessages(_)
@@ -84,6 +125,8 @@ pub(crate) async fn handle_request(runner: &Runner, req: RunnerRequest) -> Runne
         | RunnerRequest::AgentGetCommands(_)
         | RunnerRequest::PiBash(_)
         | RunnerRequest::PiAbortBash(_)
-        | RunnerRequest::PiExtensionUiResponse(_)) => super::pi::handle_request(runner, req).await,
+        | RunnerRequest::PiExtensionUiResponse(_)
+        | RunnerRequest::ListToolApprovals(_)
+        | RunnerRequest::ResolveToolApproval(_)) => super::pi::handle_request(runner, req).await,
     }
 }
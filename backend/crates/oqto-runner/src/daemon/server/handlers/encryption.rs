@@ -0,0 +1,8 @@
+use super::super::*;
+
+pub(crate) async fn handle_request(runner: &Runner, req: RunnerRequest) -> RunnerResponse {
+    match req {
+        RunnerRequest::WorkspaceEncryption(r) => runner.workspace_encryption(r).await,
+        _ => error_response(ErrorCode::InvalidRequest, "Invalid encryption request"),
+    }
+}
@@ -0,0 +1,9 @@
+use super::super::*;
+
+pub(crate) async fn handle_request(runner: &Runner, req: RunnerRequest) -> RunnerResponse {
+    match req {
+        RunnerRequest::ListFileVersions(r) => runner.list_file_versions(r).await,
+        RunnerRequest::ReadFileVersion(r) => runner.read_file_version(r).await,
+        _ => error_response(ErrorCode::InvalidRequest, "Invalid file history request"),
+    }
+}
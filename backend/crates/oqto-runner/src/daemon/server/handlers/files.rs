@@ -8,6 +8,7 @@ pub(crate) async fn handle_request(runner: &Runner, req: RunnerRequest) -> Runne
         RunnerRequest::Stat(r) => runner.stat(r).await,
         RunnerRequest::DeletePath(r) => runner.delete_path(r).await,
         RunnerRequest::CreateDirectory(r) => runner.create_directory(r).await,
+        RunnerRequest::ExtractArchive(r) => runner.extract_archive(r).await,
         _ => error_response(ErrorCode::InvalidRequest, "Invalid files request"),
     }
 }
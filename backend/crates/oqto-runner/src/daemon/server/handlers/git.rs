@@ -0,0 +1,14 @@
+use super::super::*;
+
+pub(crate) async fn handle_request(runner: &Runner, req: RunnerRequest) -> RunnerResponse {
+    match req {
+        RunnerRequest::GitStatus(r) => runner.git_status(r).await,
+        RunnerRequest::GitDiff(r) => runner.git_diff(r).await,
+        RunnerRequest::GitLog(r) => runner.git_log(r).await,
+        RunnerRequest::GitBranches(r) => runner.git_branches(r).await,
+        RunnerRequest::GitSwitchBranch(r) => runner.git_switch_branch(r).await,
+        RunnerRequest::GitCommit(r) => runner.git_commit(r).await,
+        RunnerRequest::GitPush(r) => runner.git_push(r).await,
+        _ => error_response(ErrorCode::InvalidRequest, "Invalid git request"),
+    }
+}
@@ -1,7 +1,15 @@
+pub mod background;
+pub mod data_query;
+pub mod dependency_scan;
+pub mod diagnostics;
 pub mod dispatch;
+pub mod encryption;
+pub mod file_history;
 pub mod files;
+pub mod git;
 pub mod memories;
 pub mod pi;
 pub mod process;
 pub mod sessions;
+pub mod smoke_check;
 pub mod trx;
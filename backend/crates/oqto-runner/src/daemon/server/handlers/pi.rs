@@ -16,6 +16,7 @@ pub(crate) async fn handle_request(runner: &Runner, req: RunnerRequest) -> Runne
         RunnerRequest::PiPrompt(r) => runner.pi_prompt(r).await,
         RunnerRequest::PiSteer(r) => runner.pi_steer(r).await,
         RunnerRequest::PiFollowUp(r) => runner.pi_follow_up(r).await,
+        RunnerRequest::PiInterject(r) => runner.pi_interject(r).await,
         RunnerRequest::PiAbort(r) => runner.pi_abort(r).await,
         RunnerRequest::PiGetState(r) => runner.pi_get_state(r).await,
         RunnerRequest::PiGetMessages(r) => runner.pi_get_messages(r).await,
@@ -40,6 +41,8 @@ pub(crate) async fn handle_request(runner: &Runner, req: RunnerRequest) -> Runne
         RunnerRequest::PiBash(r) => runner.pi_bash(r).await,
         RunnerRequest::PiAbortBash(r) => runner.pi_abort_bash(r).await,
         RunnerRequest::PiExtensionUiResponse(r) => runner.pi_extension_ui_response(r).await,
+        RunnerRequest::ListToolApprovals(r) => runner.list_tool_approvals(r).await,
+        RunnerRequest::ResolveToolApproval(r) => runner.resolve_tool_approval(r).await,
         _ => error_response(ErrorCode::InvalidRequest, "Invalid pi request"),
     }
 }
@@ -6,6 +6,7 @@ pub(crate) async fn handle_request(runner: &Runner, req: RunnerRequest) -> Runne
         RunnerRequest::GetSession(r) => runner.get_session(r).await,
         RunnerRequest::StartSession(r) => runner.start_session(r).await,
         RunnerRequest::StopSession(r) => runner.stop_session(r).await,
+        RunnerRequest::GetSessionResources(r) => runner.get_session_resources(r).await,
         RunnerRequest::ListMainChatSessions => runner.list_main_chat_sessions().await,
         RunnerRequest::GetMainChatMessages(r) => runner.get_main_chat_messages(r).await,
         RunnerRequest::GetWorkspaceChatMessages(r) => runner.get_workspace_chat_messages(r).await,
@@ -20,6 +21,7 @@ pub(crate) async fn handle_request(runner: &Runner, req: RunnerRequest) -> Runne
         RunnerRequest::RepairWorkspaceChatHistory(r) => {
             runner.repair_workspace_chat_history(r).await
         }
+        RunnerRequest::CheckHistoryIntegrity(r) => runner.check_history_integrity(r).await,
         _ => error_response(ErrorCode::InvalidRequest, "Invalid sessions request"),
     }
 }
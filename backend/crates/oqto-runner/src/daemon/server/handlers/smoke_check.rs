@@ -0,0 +1,8 @@
+use super::super::*;
+
+pub(crate) async fn handle_request(runner: &Runner, req: RunnerRequest) -> RunnerResponse {
+    match req {
+        RunnerRequest::RunSmokeCheck(r) => runner.run_smoke_check(r).await,
+        _ => error_response(ErrorCode::InvalidRequest, "Invalid smoke check request"),
+    }
+}
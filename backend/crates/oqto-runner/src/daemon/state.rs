@@ -1,10 +1,12 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::path::PathBuf;
 use std::sync::Arc;
 
 use tokio::process::Child;
 use tokio::sync::{Mutex, RwLock, broadcast};
 
+use crate::background::BackgroundProcess;
+
 /// Session state tracked by the runner.
 #[derive(Debug, Clone)]
 #[allow(dead_code)]
@@ -16,6 +18,10 @@ pub struct SessionState {
     pub fileserver_port: u16,
     pub ttyd_port: u16,
     pub agent: Option<String>,
+    /// Shell environment the workspace declares.
+    pub shell: Option<crate::protocol::ShellEnvironment>,
+    /// Secret variables, including where the secret files are.
+    pub secret_env: HashMap<String, String>,
     pub started_at: std::time::Instant,
 }
 
@@ -80,6 +86,12 @@ impl ManagedProcess {
 pub struct RunnerState {
     pub processes: HashMap<String, ManagedProcess>,
     pub sessions: HashMap<String, SessionState>,
+    /// Encrypted workspaces this runner has unlocked and will lock again once
+    /// no Pi session is using them.
+    pub unlocked_workspaces: HashSet<PathBuf>,
+    /// Agent background processes by ID; their current runs are in
+    /// `processes` under the same ID.
+    pub background: HashMap<String, BackgroundProcess>,
 }
 
 impl RunnerState {
@@ -87,6 +99,8 @@ impl RunnerState {
         Self {
             processes: HashMap::new(),
             sessions: HashMap::new(),
+            unlocked_workspaces: HashSet::new(),
+            background: HashMap::new(),
         }
     }
 }
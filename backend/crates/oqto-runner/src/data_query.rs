@@ -0,0 +1,387 @@
+//! Read-only SQL queries over workspace data files.
+//!
+//! The runner runs the `duckdb` CLI as the workspace's user, inside the
+//! workspace, so CSV, Parquet and JSON files can be queried by relative path
+//! (`SELECT * FROM 'data/events.parquet'`) and SQLite databases through
+//! `sqlite_scan` when DuckDB is built with its sqlite extension. Only a
+//! single query is accepted and it runs as a subquery, so statements that
+//! write or change settings (`COPY`, `ATTACH`, `INSTALL`, `SET`, ...) cannot
+//! run.
+//!
+//! The confinement does not rest on that check alone: before the query,
+//! DuckDB is limited to the workspace directory (`allowed_directories` with
+//! `enable_external_access` off, which also rules out network access and
+//! extension loading) and its configuration is locked, so even a statement
+//! slipping past [`single_statement`] cannot read other files or lift the
+//! limits.
+
+use std::path::Path;
+use std::process::Stdio;
+use std::time::{Duration, Instant};
+
+use log::{info, warn};
+use serde_json::Value;
+
+use crate::dependency_scan::{read_capped, tail};
+use crate::protocol::{DataQueryColumn, DataQueryResponse};
+
+/// Rows returned when the request sets no limit.
+pub const DEFAULT_MAX_ROWS: usize = 1000;
+
+/// Upper bound for a requested row limit.
+const MAX_ROWS: usize = 100_000;
+
+/// Time limit when the request sets none.
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Upper bound for a requested time limit.
+const MAX_TIMEOUT: Duration = Duration::from_secs(300);
+
+/// Output beyond this is dropped and the result marked truncated. The
+/// whole result is held in memory, here and again in the backend, before
+/// the first row goes out.
+const MAX_OUTPUT_BYTES: usize = 16 * 1024 * 1024;
+
+/// Tail of stderr kept for error messages.
+const MAX_STDERR_BYTES: usize = 8 * 1024;
+
+/// Why a query did not produce a result.
+#[derive(Debug)]
+pub enum DataQueryError {
+    /// The request is not a single query.
+    Invalid(String),
+    /// `duckdb` could not be started.
+    Unavailable(String),
+    TimedOut(Duration),
+    /// DuckDB's error output.
+    Failed(String),
+}
+
+impl std::fmt::Display for DataQueryError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Invalid(reason) => write!(f, "invalid query: {reason}"),
+            Self::Unavailable(reason) => write!(f, "duckdb is not available: {reason}"),
+            Self::TimedOut(limit) => write!(f, "query timed out after {}s", limit.as_secs()),
+            Self::Failed(message) => f.write_str(message),
+        }
+    }
+}
+
+impl std::error::Error for DataQueryError {}
+
+/// Clamp a requested row limit.
+pub fn effective_max_rows(max_rows: Option<usize>) -> usize {
+    max_rows.unwrap_or(DEFAULT_MAX_ROWS).clamp(1, MAX_ROWS)
+}
+
+/// Clamp a requested time limit.
+pub fn effective_timeout(timeout_secs: Option<u64>) -> Duration {
+    timeout_secs
+        .map(Duration::from_secs)
+        .unwrap_or(DEFAULT_TIMEOUT)
+        .clamp(Duration::from_secs(1), MAX_TIMEOUT)
+}
+
+/// The query without trailing semicolons; an error if it holds more than
+/// one statement. Semicolons inside literals (including `E'...'` escape
+/// strings and `$tag$...$tag$` dollar quoting), quoted identifiers and
+/// comments do not count. An unterminated literal or comment is an error.
+fn single_statement(sql: &str) -> Result<&str, DataQueryError> {
+    let sql = sql.trim().trim_end_matches([';', ' ', '\t', '\r', '\n']);
+    if sql.is_empty() {
+        return Err(DataQueryError::Invalid("query is empty".to_string()));
+    }
+    let unterminated = || DataQueryError::Invalid("unterminated literal or comment".to_string());
+    let bytes = sql.as_bytes();
+    let is_word = |i: usize| bytes[i].is_ascii_alphanumeric() || bytes[i] == b'_';
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            quote @ (b'\'' | b'"') => {
+                // In `E'...'`, a backslash escapes the next character. A
+                // doubled quote closes and reopens the literal either way.
+                let escapes = quote == b'\''
+                    && i > 0
+                    && bytes[i - 1].eq_ignore_ascii_case(&b'e')
+                    && (i < 2 || !is_word(i - 2));
+                i += 1;
+                while i < bytes.len() && bytes[i] != quote {
+                    i += if escapes && bytes[i] == b'\\' { 2 } else { 1 };
+                }
+                if i >= bytes.len() {
+                    return Err(unterminated());
+                }
+            }
+            b'$' if i == 0 || !is_word(i - 1) => {
+                // `$tag$ ... $tag$` with an optional tag; `$1` is a parameter.
+                let tag_end = bytes[i + 1..]
+                    .iter()
+                    .position(|&b| !(b.is_ascii_alphabetic() || b == b'_'))
+                    .map(|n| i + 1 + n);
+                if let Some(end) = tag_end.filter(|&end| bytes[end] == b'$') {
+                    let tag = &sql[i..=end];
+                    let close = sql[end + 1..].find(tag).ok_or_else(unterminated)?;
+                    i = end + close + tag.len();
+                }
+            }
+            b'-' if bytes.get(i + 1) == Some(&b'-') => {
+                while i < bytes.len() && bytes[i] != b'\n' {
+                    i += 1;
+                }
+            }
+            b'/' if bytes.get(i + 1) == Some(&b'*') => {
+                i += 2;
+                while i < bytes.len() && !(bytes[i] == b'*' && bytes.get(i + 1) == Some(&b'/')) {
+                    i += 1;
+                }
+                if i >= bytes.len() {
+                    return Err(unterminated());
+                }
+                i += 1;
+            }
+            b';' => {
+                return Err(DataQueryError::Invalid(
+                    "only a single query is allowed".to_string(),
+                ));
+            }
+            _ => {}
+        }
+        i += 1;
+    }
+    Ok(sql)
+}
+
+/// Script run by `duckdb`: settings confining it to `workspace`, then one
+/// line with the result columns and one JSON array per row. One row more
+/// than `max_rows` is fetched to tell whether the result was cut.
+fn script(workspace: &Path, sql: &str, max_rows: usize) -> String {
+    let workspace = workspace.display().to_string().replace('\'', "''");
+    // `allowed_directories` must be set while external access is still on,
+    // and nothing can be changed after `lock_configuration`.
+    format!(
+        "SET autoinstall_known_extensions = false;\n\
+         SET autoload_known_extensions = false;\n\
+         SET allowed_directories = ['{workspace}'];\n\
+         SET enable_external_access = false;\n\
+         SET enable_progress_bar = false;\n\
+         SET lock_configuration = true;\n\
+         SELECT to_json(list({{'name': column_name, 'type': column_type}})) \
+         FROM (DESCRIBE SELECT * FROM (\n{sql}\n));\n\
+         SELECT json_array(*COLUMNS(*)) FROM (\n{sql}\n) LIMIT {};\n",
+        max_rows + 1
+    )
+}
+
+/// Parse the output of [`script`]. With `cut`, the output stopped at the
+/// size limit and its last line may be incomplete.
+fn parse_output(
+    stdout: &str,
+    max_rows: usize,
+    cut: bool,
+) -> Result<DataQueryResponse, DataQueryError> {
+    let complete = if cut {
+        stdout.rsplit_once('\n').map_or("", |(head, _)| head)
+    } else {
+        stdout
+    };
+    let mut lines = complete.lines();
+    let columns: Vec<DataQueryColumn> = lines
+        .next()
+        .and_then(|line| serde_json::from_str(line).ok())
+        .ok_or_else(|| DataQueryError::Failed("duckdb returned no result columns".to_string()))?;
+    let mut rows = Vec::new();
+    let mut truncated = cut;
+    for line in lines {
+        if rows.len() == max_rows {
+            truncated = true;
+            break;
+        }
+        let row: Vec<Value> = serde_json::from_str(line)
+            .map_err(|e| DataQueryError::Failed(format!("unexpected output from duckdb: {e}")))?;
+        rows.push(row);
+    }
+    Ok(DataQueryResponse {
+        columns,
+        rows,
+        truncated,
+        elapsed_ms: 0,
+    })
+}
+
+/// Run `sql` in `workspace`.
+pub async fn query(
+    workspace: &Path,
+    sql: &str,
+    max_rows: usize,
+    timeout: Duration,
+) -> Result<DataQueryResponse, DataQueryError> {
+    let sql = single_statement(sql)?;
+    let started = Instant::now();
+    let mut child = tokio::process::Command::new("duckdb")
+        // Ignore ~/.duckdbrc: it could change the output mode.
+        .args(["-init", "/dev/null", "-bail", "-list", "-noheader", "-c"])
+        .arg(script(workspace, sql, max_rows))
+        .current_dir(workspace)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true)
+        .spawn()
+        .map_err(|e| DataQueryError::Unavailable(e.to_string()))?;
+
+    let mut stdout = child.stdout.take().expect("stdout is piped");
+    let mut stderr = child.stderr.take().expect("stderr is piped");
+    let collect = async {
+        let mut out = Vec::new();
+        let mut err = Vec::new();
+        let (_, _, status) = tokio::join!(
+            read_capped(&mut stdout, &mut out, MAX_OUTPUT_BYTES),
+            read_capped(&mut stderr, &mut err, usize::MAX),
+            child.wait()
+        );
+        (out, err, status)
+    };
+    let Ok((out, err, status)) = tokio::time::timeout(timeout, collect).await else {
+        warn!(
+            "Data query timed out after {}s in {}",
+            timeout.as_secs(),
+            workspace.display()
+        );
+        return Err(DataQueryError::TimedOut(timeout));
+    };
+    if !status.is_ok_and(|s| s.success()) {
+        let message = tail(&err, MAX_STDERR_BYTES).trim().to_string();
+        return Err(DataQueryError::Failed(if message.is_empty() {
+            "duckdb failed".to_string()
+        } else {
+            message
+        }));
+    }
+
+    let cut = out.len() >= MAX_OUTPUT_BYTES;
+    let mut result = parse_output(&String::from_utf8_lossy(&out), max_rows, cut)?;
+    result.elapsed_ms = started.elapsed().as_millis() as u64;
+    info!(
+        "Data query returned {} rows in {} ms for {}",
+        result.rows.len(),
+        result.elapsed_ms,
+        workspace.display()
+    );
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_single_statements_pass() {
+        assert_eq!(
+            single_statement(" SELECT 1;\n ").unwrap(),
+            "SELECT 1",
+            "trailing semicolons are dropped"
+        );
+        assert!(single_statement("SELECT * FROM 'a;b.csv' WHERE x = 'it''s;'").is_ok());
+        assert!(single_statement("SELECT \"odd;name\" FROM t -- a; b\n").is_ok());
+        assert!(single_statement("SELECT /* ; */ 1").is_ok());
+        assert!(single_statement("SELECT 1; COPY t TO 'x.csv'").is_err());
+        assert!(single_statement("SELECT 1) ; ATTACH 'x.db' AS x; SELECT (1").is_err());
+        assert!(single_statement(" ; ").is_err());
+    }
+
+    #[test]
+    fn smuggled_statements_are_found() {
+        // The escaped quote does not end the literal, so the `;` is outside.
+        assert!(single_statement(r"SELECT E'\'' ; COPY t TO 'x.csv' --'").is_err());
+        assert!(single_statement(r"SELECT E'it\'s;' FROM t").is_ok());
+        // Outside an escape string a backslash is an ordinary character.
+        assert!(single_statement(r"SELECT 'C:\' ; SELECT 1").is_err());
+        assert!(single_statement("SELECT $$a;b$$, $x$ $$; $x$ FROM t").is_ok());
+        assert!(single_statement("SELECT $$a$$; ATTACH 'x.db'").is_err());
+        assert!(single_statement("SELECT $1, a$b FROM t; SELECT 2").is_err());
+        assert!(single_statement("SELECT $$; SELECT 1").is_err());
+        assert!(single_statement("SELECT 'open; SELECT 1").is_err());
+        assert!(single_statement("SELECT 1 /* ; SELECT 2").is_err());
+    }
+
+    #[test]
+    fn script_confines_duckdb_to_the_workspace() {
+        let script = script(Path::new("/home/al'ice/w"), "SELECT 1", 10);
+        let settings: Vec<&str> = script
+            .lines()
+            .take_while(|line| line.starts_with("SET "))
+            .collect();
+        assert_eq!(
+            settings,
+            [
+                "SET autoinstall_known_extensions = false;",
+                "SET autoload_known_extensions = false;",
+                "SET allowed_directories = ['/home/al''ice/w'];",
+                "SET enable_external_access = false;",
+                "SET enable_progress_bar = false;",
+                "SET lock_configuration = true;",
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn files_outside_the_workspace_cannot_be_read() {
+        let root = tempfile::tempdir().unwrap();
+        let workspace = root.path().canonicalize().unwrap().join("w");
+        std::fs::create_dir(&workspace).unwrap();
+        std::fs::write(workspace.join("data.csv"), "id\n1\n").unwrap();
+        std::fs::write(root.path().join("outside.csv"), "id\n1\n").unwrap();
+        let timeout = Duration::from_secs(30);
+        match query(&workspace, "SELECT * FROM 'data.csv'", 10, timeout).await {
+            Ok(result) => assert_eq!(result.rows.len(), 1),
+            // Needs the duckdb CLI.
+            Err(DataQueryError::Unavailable(_)) => return,
+            Err(e) => panic!("{e}"),
+        }
+        for sql in [
+            "SELECT * FROM read_text('/etc/passwd')",
+            "SELECT * FROM read_csv('/etc/hosts')",
+            "SELECT * FROM '../outside.csv'",
+        ] {
+            assert!(query(&workspace, sql, 10, timeout).await.is_err(), "{sql}");
+        }
+    }
+
+    #[test]
+    fn limits_are_clamped() {
+        assert_eq!(effective_max_rows(None), DEFAULT_MAX_ROWS);
+        assert_eq!(effective_max_rows(Some(0)), 1);
+        assert_eq!(effective_max_rows(Some(usize::MAX)), MAX_ROWS);
+        assert_eq!(effective_timeout(None), DEFAULT_TIMEOUT);
+        assert_eq!(effective_timeout(Some(99_999)), MAX_TIMEOUT);
+    }
+
+    #[test]
+    fn output_is_parsed_and_cut_at_the_limit() {
+        let output = "[{\"name\":\"id\",\"type\":\"BIGINT\"},{\"name\":\"city\",\"type\":\"VARCHAR\"}]\n\
+                      [1,\"Berlin\"]\n[2,null]\n[3,\"Oslo\"]\n";
+        let result = parse_output(output, 2, false).unwrap();
+        assert_eq!(result.columns.len(), 2);
+        assert_eq!(result.columns[1].data_type, "VARCHAR");
+        assert_eq!(
+            result.rows,
+            vec![
+                vec![Value::from(1), Value::from("Berlin")],
+                vec![Value::from(2), Value::Null],
+            ]
+        );
+        assert!(result.truncated);
+
+        let result = parse_output(output, 3, false).unwrap();
+        assert_eq!(result.rows.len(), 3);
+        assert!(!result.truncated);
+
+        // A line cut by the size limit is dropped.
+        let result = parse_output(&output[..output.len() - 4], 10, true).unwrap();
+        assert_eq!(result.rows.len(), 2);
+        assert!(result.truncated);
+
+        assert!(parse_output("", 10, false).is_err());
+    }
+}
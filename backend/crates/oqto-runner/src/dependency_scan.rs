@@ -0,0 +1,241 @@
+//! Vulnerability scans of workspace dependencies.
+//!
+//! The backend asks for a scan after lockfiles change (or on demand); the
+//! runner invokes the requested scanners as the workspace's user, inside the
+//! workspace, and hands their JSON reports back unparsed. Only the fixed
+//! command lines below are ever run, and none of them executes project code
+//! (`npm audit` is limited to the lockfile).
+
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use std::time::{Duration, Instant};
+
+use log::{debug, info, warn};
+use tokio::io::AsyncReadExt;
+
+use crate::protocol::{DependencyScanner, DependencyScannerRun};
+
+/// Time limit per scanner when the request sets none.
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(300);
+
+/// Upper bound for a requested time limit.
+const MAX_TIMEOUT: Duration = Duration::from_secs(1800);
+
+/// Largest report kept from a scanner's stdout.
+const MAX_STDOUT_BYTES: usize = 16 * 1024 * 1024;
+
+/// Tail of stderr kept for diagnostics.
+const MAX_STDERR_BYTES: usize = 8 * 1024;
+
+/// Clamp a requested time limit.
+pub fn effective_timeout(timeout_secs: Option<u64>) -> Duration {
+    timeout_secs
+        .map(Duration::from_secs)
+        .unwrap_or(DEFAULT_TIMEOUT)
+        .clamp(Duration::from_secs(1), MAX_TIMEOUT)
+}
+
+/// Program (looked up on PATH) and arguments of a scanner.
+fn command_line(scanner: DependencyScanner) -> (&'static str, &'static [&'static str]) {
+    match scanner {
+        DependencyScanner::OsvScanner => ("osv-scanner", &["--format", "json", "--recursive", "."]),
+        DependencyScanner::CargoAudit => ("cargo", &["audit", "--json"]),
+        DependencyScanner::NpmAudit => ("npm", &["audit", "--json", "--package-lock-only"]),
+    }
+}
+
+/// Binary whose presence tells whether a scanner is installed.
+fn probe_binary(scanner: DependencyScanner) -> &'static str {
+    match scanner {
+        DependencyScanner::OsvScanner => "osv-scanner",
+        DependencyScanner::CargoAudit => "cargo-audit",
+        DependencyScanner::NpmAudit => "npm",
+    }
+}
+
+/// Why a scanner does not apply to a workspace, if it does not.
+fn skip_reason(scanner: DependencyScanner, workspace: &Path) -> Option<String> {
+    let lockfile = match scanner {
+        DependencyScanner::OsvScanner => None,
+        DependencyScanner::CargoAudit => Some("Cargo.lock"),
+        DependencyScanner::NpmAudit => Some("package-lock.json"),
+    };
+    if let Some(lockfile) = lockfile
+        && !workspace.join(lockfile).is_file()
+    {
+        return Some(format!("no {lockfile} in workspace root"));
+    }
+    let binary = probe_binary(scanner);
+    if which_on_path(binary).is_none() {
+        return Some(format!("{binary} not found on PATH"));
+    }
+    None
+}
+
+/// Find a binary on `PATH`.
+fn which_on_path(name: &str) -> Option<PathBuf> {
+    let path = std::env::var_os("PATH")?;
+    std::env::split_paths(&path)
+        .map(|dir| dir.join(name))
+        .find(|candidate| candidate.is_file())
+}
+
+/// Run each scanner over `workspace`, one after another.
+pub async fn scan(
+    workspace: &Path,
+    scanners: &[DependencyScanner],
+    timeout: Duration,
+) -> Vec<DependencyScannerRun> {
+    let mut runs = Vec::with_capacity(scanners.len());
+    for &scanner in scanners {
+        if runs
+            .iter()
+            .any(|r: &DependencyScannerRun| r.scanner == scanner)
+        {
+            continue;
+        }
+        if let Some(reason) = skip_reason(scanner, workspace) {
+            debug!(
+                "Skipping {:?} for {}: {}",
+                scanner,
+                workspace.display(),
+                reason
+            );
+            runs.push(DependencyScannerRun {
+                scanner,
+                skipped: Some(reason),
+                exit_code: None,
+                stdout: String::new(),
+                stderr: String::new(),
+                timed_out: false,
+                truncated: false,
+                duration_ms: 0,
+            });
+            continue;
+        }
+        runs.push(run_scanner(scanner, workspace, timeout).await);
+    }
+    runs
+}
+
+async fn run_scanner(
+    scanner: DependencyScanner,
+    workspace: &Path,
+    timeout: Duration,
+) -> DependencyScannerRun {
+    let (program, args) = command_line(scanner);
+    let started = Instant::now();
+    let mut run = DependencyScannerRun {
+        scanner,
+        skipped: None,
+        exit_code: None,
+        stdout: String::new(),
+        stderr: String::new(),
+        timed_out: false,
+        truncated: false,
+        duration_ms: 0,
+    };
+
+    let mut child = match tokio::process::Command::new(program)
+        .args(args)
+        .current_dir(workspace)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true)
+        .spawn()
+    {
+        Ok(child) => child,
+        Err(e) => {
+            warn!("Failed to start {}: {}", program, e);
+            run.skipped = Some(format!("failed to start {program}: {e}"));
+            return run;
+        }
+    };
+
+    let mut stdout = child.stdout.take().expect("stdout is piped");
+    let mut stderr = child.stderr.take().expect("stderr is piped");
+    let collect = async {
+        let mut out = Vec::new();
+        let mut err = Vec::new();
+        let (_, _, status) = tokio::join!(
+            read_capped(&mut stdout, &mut out, MAX_STDOUT_BYTES),
+            read_capped(&mut stderr, &mut err, usize::MAX),
+            child.wait()
+        );
+        (out, err, status)
+    };
+
+    match tokio::time::timeout(timeout, collect).await {
+        Ok((out, err, status)) => {
+            run.truncated = out.len() >= MAX_STDOUT_BYTES;
+            run.stdout = String::from_utf8_lossy(&out).into_owned();
+            run.stderr = tail(&err, MAX_STDERR_BYTES);
+            run.exit_code = status.ok().and_then(|s| s.code());
+        }
+        Err(_) => {
+            warn!(
+                "{} timed out after {}s in {}",
+                program,
+                timeout.as_secs(),
+                workspace.display()
+            );
+            run.timed_out = true;
+        }
+    }
+    run.duration_ms = started.elapsed().as_millis() as u64;
+    info!(
+        "{:?} finished in {} ms for {} (exit {:?})",
+        scanner,
+        run.duration_ms,
+        workspace.display(),
+        run.exit_code
+    );
+    run
+}
+
+/// Read a stream to the end, keeping at most `limit` bytes.
+pub(crate) async fn read_capped(
+    reader: &mut (impl tokio::io::AsyncRead + Unpin),
+    out: &mut Vec<u8>,
+    limit: usize,
+) {
+    let mut buf = [0u8; 8192];
+    loop {
+        match reader.read(&mut buf).await {
+            Ok(0) | Err(_) => break,
+            Ok(n) => {
+                let room = limit.saturating_sub(out.len());
+                out.extend_from_slice(&buf[..n.min(room)]);
+            }
+        }
+    }
+}
+
+/// Last `limit` bytes of `bytes` as text.
+pub(crate) fn tail(bytes: &[u8], limit: usize) -> String {
+    let start = bytes.len().saturating_sub(limit);
+    String::from_utf8_lossy(&bytes[start..]).into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lockfile_scanners_need_their_lockfile() {
+        let dir = tempfile::tempdir().unwrap();
+        let reason = skip_reason(DependencyScanner::CargoAudit, dir.path()).unwrap();
+        assert!(reason.contains("Cargo.lock"));
+        let reason = skip_reason(DependencyScanner::NpmAudit, dir.path()).unwrap();
+        assert!(reason.contains("package-lock.json"));
+    }
+
+    #[test]
+    fn timeout_is_clamped() {
+        assert_eq!(effective_timeout(None), DEFAULT_TIMEOUT);
+        assert_eq!(effective_timeout(Some(0)), Duration::from_secs(1));
+        assert_eq!(effective_timeout(Some(99_999)), MAX_TIMEOUT);
+        assert_eq!(tail(b"abcdef", 3), "def");
+    }
+}
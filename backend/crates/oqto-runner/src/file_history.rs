@@ -0,0 +1,817 @@
+//! Earlier versions of workspace files.
+//!
+//! Before a prompt starts an agent turn, and when the turn ends, the runner
+//! commits the workspace into a shadow git repository kept outside of it
+//! (`<state>/oqto/file-history/<hash>.git`, with the workspace as work
+//! tree), so the state before and after each turn's edits can be read back
+//! even when the agent never commits. The workspace's `.gitignore` rules
+//! apply, as do the excludes below; files over `max_file_bytes` are left
+//! out and turns that change nothing record no snapshot. Snapshots beyond
+//! `keep` or older than `retention_days` are pruned by recommitting the rest
+//! onto a new root and garbage collecting the shadow repository. When the
+//! workspace is (inside) a git repository, its own commits are offered as
+//! versions as well.
+
+use std::collections::HashMap;
+use std::ffi::OsStr;
+use std::os::unix::ffi::OsStrExt;
+use std::path::{Component, Path, PathBuf};
+use std::process::{Output, Stdio};
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::{Context, Result, bail};
+use log::{debug, info};
+use serde::{Deserialize, Serialize};
+use tokio::io::AsyncWriteExt;
+use tokio::process::Command;
+use tokio::sync::Mutex;
+
+use crate::protocol::{FileVersion, FileVersionSelector, FileVersionSource};
+
+/// Versions listed when the request sets no limit.
+pub const DEFAULT_LIST_LIMIT: usize = 50;
+
+/// Upper bound for a requested limit.
+const MAX_LIST_LIMIT: usize = 500;
+
+/// Largest historical file served.
+const MAX_CONTENT_BYTES: u64 = 16 * 1024 * 1024;
+
+/// Never snapshotted, in addition to the workspace's `.gitignore`.
+const EXCLUDES: &[&str] = &[
+    "node_modules/",
+    "target/",
+    ".venv/",
+    "__pycache__/",
+    ".oqto/",
+    "*.sqlite",
+    "*.db",
+];
+
+/// Trailer naming the session a snapshot was taken for.
+const SESSION_TRAILER: &str = "Oqto-Session: ";
+
+/// Expired snapshots are pruned at the latest this long after they expire.
+const PRUNE_GRACE_SECS: i64 = 24 * 60 * 60;
+
+/// Per-turn workspace snapshots (`[runner.file_history]`).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FileHistoryConfig {
+    /// Snapshot workspaces around agent turns.
+    pub enabled: bool,
+    /// Larger files are left out of snapshots.
+    pub max_file_bytes: u64,
+    /// Snapshots kept per workspace; the oldest are pruned first.
+    pub keep: usize,
+    /// Days a snapshot is kept; 0 keeps it until `keep` prunes it.
+    pub retention_days: u64,
+    /// Longest a prompt waits for the snapshot taken before its turn; the
+    /// snapshot finishes in the background after that.
+    pub snapshot_timeout_secs: u64,
+}
+
+impl Default for FileHistoryConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            max_file_bytes: 10 * 1024 * 1024,
+            keep: 500,
+            retention_days: 30,
+            snapshot_timeout_secs: 10,
+        }
+    }
+}
+
+/// Shadow repositories of the workspaces a runner has snapshotted.
+#[derive(Debug)]
+pub struct FileHistoryStore {
+    dir: PathBuf,
+    max_file_bytes: u64,
+    keep: usize,
+    retention: Option<Duration>,
+    snapshot_timeout: Duration,
+    /// Serializes snapshots of a workspace; concurrent commits would race on
+    /// its index.
+    locks: std::sync::Mutex<HashMap<PathBuf, Arc<Mutex<()>>>>,
+}
+
+impl FileHistoryStore {
+    pub fn new(dir: PathBuf, config: &FileHistoryConfig) -> Self {
+        Self {
+            dir,
+            max_file_bytes: config.max_file_bytes,
+            keep: config.keep.max(1),
+            retention: (config.retention_days > 0)
+                .then(|| Duration::from_secs(config.retention_days * 24 * 60 * 60)),
+            snapshot_timeout: Duration::from_secs(config.snapshot_timeout_secs),
+            locks: std::sync::Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Longest a prompt waits for the snapshot taken before its turn.
+    pub fn snapshot_timeout(&self) -> Duration {
+        self.snapshot_timeout
+    }
+
+    fn lock(&self, git_dir: &Path) -> Arc<Mutex<()>> {
+        let mut locks = self.locks.lock().unwrap_or_else(|e| e.into_inner());
+        Arc::clone(locks.entry(git_dir.to_path_buf()).or_default())
+    }
+
+    /// Shadow repository of a workspace.
+    fn repo_dir(&self, workspace: &Path) -> PathBuf {
+        // FNV-1a: stable across toolchains, unlike `DefaultHasher`.
+        let hash = workspace
+            .as_os_str()
+            .as_encoded_bytes()
+            .iter()
+            .fold(0xcbf2_9ce4_8422_2325_u64, |h, b| {
+                (h ^ u64::from(*b)).wrapping_mul(0x0100_0000_01b3)
+            });
+        self.dir.join(format!("{hash:016x}.git"))
+    }
+
+    /// Commit the current state of `workspace`. Returns the snapshot's
+    /// commit, or None when nothing changed since the last one.
+    pub async fn snapshot(
+        &self,
+        workspace: &Path,
+        label: &str,
+        session_id: Option<&str>,
+    ) -> Result<Option<String>> {
+        let lock = self.lock(&self.repo_dir(workspace));
+        let _guard = lock.lock().await;
+        let git_dir = self.ensure_repo(workspace).await?;
+        let shadow = |args: &[&str]| shadow_git(&git_dir, workspace, args);
+
+        let mut pathspecs = b".\0".to_vec();
+        for path in self.oversized(&git_dir, workspace).await? {
+            debug!(
+                "Snapshot of {} leaves out {} (over {} bytes)",
+                workspace.display(),
+                path.display(),
+                self.max_file_bytes
+            );
+            pathspecs.extend_from_slice(b":(exclude,literal)");
+            pathspecs.extend_from_slice(path.as_os_str().as_bytes());
+            pathspecs.push(0);
+        }
+        // Unreadable files are skipped rather than failing the snapshot.
+        let add = shadow_git_input(
+            &git_dir,
+            workspace,
+            &[
+                "add",
+                "-A",
+                "--ignore-errors",
+                "--pathspec-from-file=-",
+                "--pathspec-file-nul",
+            ],
+            &pathspecs,
+        )
+        .await?;
+        if !add.status.success() {
+            debug!(
+                "Snapshot of {} skipped files: {}",
+                workspace.display(),
+                String::from_utf8_lossy(&add.stderr).trim()
+            );
+        }
+        let has_head = shadow(&["rev-parse", "-q", "--verify", "HEAD"])
+            .await?
+            .status
+            .success();
+        if has_head
+            && shadow(&["diff", "--cached", "--quiet", "HEAD"])
+                .await?
+                .status
+                .success()
+        {
+            return Ok(None);
+        }
+
+        let mut message = label.to_string();
+        if let Some(session_id) = session_id {
+            message.push_str(&format!("\n\n{SESSION_TRAILER}{session_id}"));
+        }
+        let commit = shadow(&[
+            "-c",
+            "user.name=oqto",
+            "-c",
+            "user.email=oqto@localhost",
+            "-c",
+            "commit.gpgsign=false",
+            "commit",
+            "-q",
+            "--no-verify",
+            "-m",
+            &message,
+        ])
+        .await?;
+        if !commit.status.success() {
+            bail!(
+                "snapshot commit failed: {}",
+                String::from_utf8_lossy(&commit.stderr).trim()
+            );
+        }
+        let head = checked(shadow(&["rev-parse", "HEAD"]).await?, "rev-parse")?;
+        let head = head.trim().to_string();
+
+        if self.prune(&git_dir, workspace).await? {
+            // Pruning recommits the kept snapshots, including this one.
+            let head = checked(shadow(&["rev-parse", "HEAD"]).await?, "rev-parse")?;
+            return Ok(Some(head.trim().to_string()));
+        }
+        Ok(Some(head))
+    }
+
+    /// New and changed workspace files larger than `max_file_bytes`.
+    async fn oversized(&self, git_dir: &Path, workspace: &Path) -> Result<Vec<PathBuf>> {
+        let output = shadow_git(
+            git_dir,
+            workspace,
+            &[
+                "ls-files",
+                "-z",
+                "--others",
+                "--modified",
+                "--exclude-standard",
+            ],
+        )
+        .await?;
+        if !output.status.success() {
+            bail!(
+                "git ls-files failed: {}",
+                String::from_utf8_lossy(&output.stderr).trim()
+            );
+        }
+        let mut oversized = Vec::new();
+        for path in output.stdout.split(|b| *b == 0).filter(|p| !p.is_empty()) {
+            let path = Path::new(OsStr::from_bytes(path));
+            // Deleted files have no metadata; symlinks are stored as links.
+            if let Ok(metadata) = tokio::fs::symlink_metadata(workspace.join(path)).await
+                && metadata.is_file()
+                && metadata.len() > self.max_file_bytes
+            {
+                oversized.push(path.to_path_buf());
+            }
+        }
+        Ok(oversized)
+    }
+
+    /// Drop snapshots beyond `keep` or past the retention period once
+    /// enough are due. The kept snapshots are recommitted onto a new root,
+    /// which changes their ids, and the rest is garbage collected. Returns
+    /// whether anything was pruned.
+    async fn prune(&self, git_dir: &Path, workspace: &Path) -> Result<bool> {
+        let shadow = |args: &[&str]| shadow_git(git_dir, workspace, args);
+        let log = checked(
+            shadow(&["log", "--format=%T%x1f%ct%x1f%B%x1e"]).await?,
+            "git log",
+        )?;
+        let snapshots: Vec<(&str, i64, &str)> = log
+            .split('\u{1e}')
+            .filter_map(|entry| {
+                let mut fields = entry.trim_start().splitn(3, '\u{1f}');
+                let tree = fields.next().filter(|t| !t.is_empty())?;
+                let timestamp = fields.next()?.parse().ok()?;
+                Some((tree, timestamp, fields.next()?.trim()))
+            })
+            .collect();
+        let timestamps: Vec<i64> = snapshots.iter().map(|(_, ts, _)| *ts).collect();
+        let now = chrono::Utc::now().timestamp();
+        let Some(kept) = snapshots_to_keep(&timestamps, self.keep, self.retention, now) else {
+            return Ok(false);
+        };
+
+        let mut parent: Option<String> = None;
+        for (tree, timestamp, message) in snapshots[..kept].iter().rev() {
+            let date = format!("@{timestamp} +0000");
+            let mut command = shadow_command(git_dir, workspace);
+            command
+                .args(["commit-tree", tree, "-m", message])
+                .env("GIT_AUTHOR_NAME", "oqto")
+                .env("GIT_AUTHOR_EMAIL", "oqto@localhost")
+                .env("GIT_AUTHOR_DATE", &date)
+                .env("GIT_COMMITTER_NAME", "oqto")
+                .env("GIT_COMMITTER_EMAIL", "oqto@localhost")
+                .env("GIT_COMMITTER_DATE", &date);
+            if let Some(parent) = &parent {
+                command.args(["-p", parent]);
+            }
+            let output = command.output().await.context("running git")?;
+            parent = Some(checked(output, "git commit-tree")?.trim().to_string());
+        }
+        let Some(head) = parent else {
+            return Ok(false);
+        };
+        checked(
+            shadow(&["update-ref", "HEAD", &head]).await?,
+            "git update-ref",
+        )?;
+        checked(
+            shadow(&["reflog", "expire", "--expire=now", "--all"]).await?,
+            "git reflog expire",
+        )?;
+        checked(shadow(&["gc", "--prune=now", "--quiet"]).await?, "git gc")?;
+        info!(
+            "Pruned {} snapshots of {}",
+            snapshots.len() - kept,
+            workspace.display()
+        );
+        Ok(true)
+    }
+
+    /// Versions of `path` (relative to the workspace), newest first.
+    pub async fn versions(
+        &self,
+        workspace: &Path,
+        path: &str,
+        limit: Option<usize>,
+    ) -> Result<Vec<FileVersion>> {
+        let path = relative_path(path)?;
+        let limit = limit.unwrap_or(DEFAULT_LIST_LIMIT).clamp(1, MAX_LIST_LIMIT);
+        let log_args = log_args(&path, limit);
+        let log_args: Vec<&str> = log_args.iter().map(String::as_str).collect();
+
+        let mut versions = Vec::new();
+        let git_dir = self.repo_dir(workspace);
+        if git_dir.join("HEAD").is_file() {
+            let output = shadow_git(&git_dir, workspace, &log_args).await?;
+            versions.extend(parse_log(
+                &checked(output, "git log")?,
+                FileVersionSource::Snapshot,
+            ));
+        }
+        if in_git_repository(workspace).await {
+            let output = workspace_git(workspace, &log_args).await?;
+            // Paths outside the repository (ignored, untracked) have no log.
+            if output.status.success() {
+                versions.extend(parse_log(
+                    &String::from_utf8_lossy(&output.stdout),
+                    FileVersionSource::Git,
+                ));
+            }
+        }
+        versions.sort_by(|a, b| b.timestamp.cmp(&a.timestamp));
+        versions.truncate(limit);
+        Ok(versions)
+    }
+
+    /// Content of `path` at the selected version. None if there is no such
+    /// version or the file did not exist in it.
+    pub async fn read(
+        &self,
+        workspace: &Path,
+        path: &str,
+        selector: &FileVersionSelector,
+    ) -> Result<Option<(FileVersion, Vec<u8>)>> {
+        let versions = self.versions(workspace, path, Some(MAX_LIST_LIMIT)).await?;
+        let Some(version) = select(&versions, selector).cloned() else {
+            return Ok(None);
+        };
+        if version.deleted {
+            return Ok(None);
+        }
+
+        let object = format!("{}:./{}", version.commit, relative_path(path)?.display());
+        let git_dir = self.repo_dir(workspace);
+        let run = |args: Vec<String>| {
+            let git_dir = &git_dir;
+            let source = version.source;
+            async move {
+                let args: Vec<&str> = args.iter().map(String::as_str).collect();
+                match source {
+                    FileVersionSource::Snapshot => shadow_git(git_dir, workspace, &args).await,
+                    FileVersionSource::Git => workspace_git(workspace, &args).await,
+                }
+            }
+        };
+        let size = run(vec!["cat-file".into(), "-s".into(), object.clone()]).await?;
+        let size: u64 = checked(size, "cat-file -s")?
+            .trim()
+            .parse()
+            .context("parsing object size")?;
+        if size > MAX_CONTENT_BYTES {
+            bail!("file is larger than {MAX_CONTENT_BYTES} bytes at this version");
+        }
+        let blob = run(vec!["cat-file".into(), "blob".into(), object]).await?;
+        if !blob.status.success() {
+            bail!(
+                "reading version failed: {}",
+                String::from_utf8_lossy(&blob.stderr).trim()
+            );
+        }
+        Ok(Some((version, blob.stdout)))
+    }
+
+    async fn ensure_repo(&self, workspace: &Path) -> Result<PathBuf> {
+        let git_dir = self.repo_dir(workspace);
+        if git_dir.join("HEAD").is_file() {
+            return Ok(git_dir);
+        }
+        tokio::fs::create_dir_all(&self.dir)
+            .await
+            .with_context(|| format!("creating {}", self.dir.display()))?;
+        // An empty template keeps the user's hooks out of the shadow repo.
+        let output = Command::new("git")
+            .arg("init")
+            .arg("-q")
+            .arg("--bare")
+            .arg("--template=")
+            .arg(&git_dir)
+            .output()
+            .await
+            .context("running git init")?;
+        checked(output, "git init")?;
+        let info = git_dir.join("info");
+        tokio::fs::create_dir_all(&info).await?;
+        tokio::fs::write(info.join("exclude"), EXCLUDES.join("\n") + "\n").await?;
+        for (key, value) in [("core.bare", "false"), ("core.autocrlf", "false")] {
+            checked(
+                shadow_git(&git_dir, workspace, &["config", key, value]).await?,
+                "git config",
+            )?;
+        }
+        Ok(git_dir)
+    }
+}
+
+/// How many snapshots (timestamps in seconds, newest first) survive a
+/// prune, or None while too few are due to be worth rewriting the history:
+/// a tenth of `keep` over the limit, or any expired for longer than a day.
+fn snapshots_to_keep(
+    timestamps: &[i64],
+    keep: usize,
+    retention: Option<Duration>,
+    now: i64,
+) -> Option<usize> {
+    let cutoff = retention.map(|r| now - i64::try_from(r.as_secs()).unwrap_or(i64::MAX));
+    let kept = timestamps
+        .iter()
+        .take(keep)
+        .take_while(|ts| cutoff.is_none_or(|cutoff| **ts >= cutoff))
+        .count()
+        .max(1);
+    let surplus = timestamps.len().checked_sub(kept).filter(|s| *s > 0)?;
+    let overdue = cutoff
+        .zip(timestamps.last())
+        .is_some_and(|(cutoff, oldest)| *oldest < cutoff.saturating_sub(PRUNE_GRACE_SECS));
+    (surplus >= (keep / 10).max(1) || overdue).then_some(kept)
+}
+
+/// Git command against a workspace's shadow repository.
+fn shadow_command(git_dir: &Path, workspace: &Path) -> Command {
+    let mut command = Command::new("git");
+    command
+        .arg("--git-dir")
+        .arg(git_dir)
+        .arg("--work-tree")
+        .arg(workspace)
+        .current_dir(workspace)
+        .env_remove("GIT_DIR")
+        .env_remove("GIT_WORK_TREE")
+        .env_remove("GIT_INDEX_FILE");
+    command
+}
+
+/// Run git against a workspace's shadow repository.
+async fn shadow_git(git_dir: &Path, workspace: &Path, args: &[&str]) -> Result<Output> {
+    shadow_command(git_dir, workspace)
+        .args(args)
+        .output()
+        .await
+        .context("running git")
+}
+
+/// Run git against a workspace's shadow repository with `input` on stdin.
+async fn shadow_git_input(
+    git_dir: &Path,
+    workspace: &Path,
+    args: &[&str],
+    input: &[u8],
+) -> Result<Output> {
+    let mut child = shadow_command(git_dir, workspace)
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .context("running git")?;
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin.write_all(input).await.context("writing to git")?;
+    }
+    child.wait_with_output().await.context("running git")
+}
+
+/// Run git in the workspace's own repository.
+pub(crate) async fn workspace_git(workspace: &Path, args: &[&str]) -> Result<Output> {
+    Command::new("git")
+        .args(args)
+        .current_dir(workspace)
+        .env_remove("GIT_DIR")
+        .env_remove("GIT_WORK_TREE")
+        .env_remove("GIT_INDEX_FILE")
+        .output()
+        .await
+        .context("running git")
+}
+
+pub(crate) async fn in_git_repository(workspace: &Path) -> bool {
+    workspace_git(workspace, &["rev-parse", "--is-inside-work-tree"])
+        .await
+        .is_ok_and(|o| o.status.success() && o.stdout.starts_with(b"true"))
+}
+
+/// Stdout of a successful command.
+pub(crate) fn checked(output: Output, what: &str) -> Result<String> {
+    if !output.status.success() {
+        bail!(
+            "{what} failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// Reject absolute paths and `..` so a request stays inside the workspace.
+pub(crate) fn relative_path(path: &str) -> Result<PathBuf> {
+    let path = Path::new(path.trim_start_matches("./"));
+    if path.as_os_str().is_empty()
+        || path
+            .components()
+            .any(|c| !matches!(c, Component::Normal(_) | Component::CurDir))
+    {
+        bail!("path must be relative to the workspace");
+    }
+    Ok(path.to_path_buf())
+}
+
+fn log_args(path: &Path, limit: usize) -> Vec<String> {
+    vec![
+        "log".to_string(),
+        "-n".to_string(),
+        limit.to_string(),
+        "--format=%x1e%H%x1f%ct%x1f%s%x1f%b%x1f".to_string(),
+        "--name-status".to_string(),
+        "--".to_string(),
+        path.display().to_string(),
+    ]
+}
+
+/// Parse `git log` output produced with `log_args`.
+fn parse_log(output: &str, source: FileVersionSource) -> Vec<FileVersion> {
+    let prefix = match source {
+        FileVersionSource::Snapshot => "snapshot",
+        FileVersionSource::Git => "git",
+    };
+    output
+        .split('\u{1e}')
+        .filter_map(|entry| {
+            let mut fields = entry.splitn(5, '\u{1f}');
+            let commit = fields.next()?.trim().to_string();
+            let timestamp = fields.next()?.trim().parse::<i64>().ok()? * 1000;
+            let label = fields.next()?.trim().to_string();
+            let body = fields.next()?;
+            let status = fields.next().unwrap_or_default();
+            if commit.is_empty() {
+                return None;
+            }
+            let session_id = match source {
+                FileVersionSource::Snapshot => body
+                    .lines()
+                    .find_map(|l| l.trim().strip_prefix(SESSION_TRAILER))
+                    .map(ToString::to_string),
+                FileVersionSource::Git => None,
+            };
+            let deleted = status
+                .lines()
+                .find(|l| !l.trim().is_empty())
+                .is_some_and(|l| l.starts_with('D'));
+            Some(FileVersion {
+                id: format!("{prefix}:{commit}"),
+                source,
+                commit,
+                timestamp,
+                label,
+                session_id,
+                deleted,
+            })
+        })
+        .collect()
+}
+
+/// Pick a version from a newest-first list.
+fn select<'a>(
+    versions: &'a [FileVersion],
+    selector: &FileVersionSelector,
+) -> Option<&'a FileVersion> {
+    match selector {
+        FileVersionSelector::Version(id) => {
+            let id = id.trim();
+            versions.iter().find(|v| v.id == id).or_else(|| {
+                let hash = id.rsplit(':').next().unwrap_or(id);
+                let mut matches = versions
+                    .iter()
+                    .filter(|v| hash.len() >= 7 && v.commit.starts_with(hash));
+                let first = matches.next()?;
+                // Ambiguous prefixes select nothing.
+                matches.all(|v| v.commit == first.commit).then_some(first)
+            })
+        }
+        FileVersionSelector::At(at) => versions.iter().find(|v| v.timestamp <= *at),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn git_available() -> bool {
+        std::process::Command::new("git")
+            .arg("--version")
+            .output()
+            .is_ok_and(|o| o.status.success())
+    }
+
+    #[tokio::test]
+    async fn snapshots_record_file_versions() {
+        if !git_available() {
+            return;
+        }
+        let state = tempfile::tempdir().unwrap();
+        let workspace = tempfile::tempdir().unwrap();
+        let store = FileHistoryStore::new(
+            state.path().join("file-history"),
+            &FileHistoryConfig::default(),
+        );
+        let file = workspace.path().join("notes.md");
+
+        std::fs::write(&file, "one\n").unwrap();
+        std::fs::create_dir(workspace.path().join("node_modules")).unwrap();
+        std::fs::write(workspace.path().join("node_modules/x.js"), "x").unwrap();
+        let first = store
+            .snapshot(workspace.path(), "before turn", Some("ses_1"))
+            .await
+            .unwrap();
+        assert!(first.is_some());
+        // Nothing changed: no new snapshot.
+        assert!(
+            store
+                .snapshot(workspace.path(), "after turn", Some("ses_1"))
+                .await
+                .unwrap()
+                .is_none()
+        );
+        std::fs::write(&file, "two\n").unwrap();
+        store
+            .snapshot(workspace.path(), "after turn", Some("ses_1"))
+            .await
+            .unwrap()
+            .unwrap();
+        std::fs::remove_file(&file).unwrap();
+        store
+            .snapshot(workspace.path(), "after turn", None)
+            .await
+            .unwrap()
+            .unwrap();
+
+        let versions = store
+            .versions(workspace.path(), "notes.md", None)
+            .await
+            .unwrap();
+        assert_eq!(versions.len(), 3);
+        assert!(versions[0].deleted);
+        assert_eq!(versions[2].label, "before turn");
+        assert_eq!(versions[2].session_id.as_deref(), Some("ses_1"));
+        assert!(
+            store
+                .versions(workspace.path(), "node_modules/x.js", None)
+                .await
+                .unwrap()
+                .is_empty()
+        );
+
+        let selector = FileVersionSelector::Version(versions[1].id.clone());
+        let (version, content) = store
+            .read(workspace.path(), "notes.md", &selector)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(version.commit, versions[1].commit);
+        assert_eq!(content, b"two\n");
+        let deleted = FileVersionSelector::Version(versions[0].commit[..10].to_string());
+        assert!(
+            store
+                .read(workspace.path(), "notes.md", &deleted)
+                .await
+                .unwrap()
+                .is_none()
+        );
+        assert!(
+            store
+                .versions(workspace.path(), "../x", None)
+                .await
+                .is_err()
+        );
+    }
+
+    #[tokio::test]
+    async fn snapshots_leave_out_large_files_and_prune_old_ones() {
+        if !git_available() {
+            return;
+        }
+        let state = tempfile::tempdir().unwrap();
+        let workspace = tempfile::tempdir().unwrap();
+        let config = FileHistoryConfig {
+            max_file_bytes: 8,
+            keep: 2,
+            ..Default::default()
+        };
+        let store = FileHistoryStore::new(state.path().join("file-history"), &config);
+        std::fs::write(workspace.path().join("big.bin"), "0123456789").unwrap();
+
+        let mut last = None;
+        for n in 0..4 {
+            std::fs::write(workspace.path().join("notes.md"), format!("{n}\n")).unwrap();
+            last = store
+                .snapshot(workspace.path(), "after turn", Some("ses_1"))
+                .await
+                .unwrap();
+        }
+
+        assert!(
+            store
+                .versions(workspace.path(), "big.bin", None)
+                .await
+                .unwrap()
+                .is_empty()
+        );
+        let versions = store
+            .versions(workspace.path(), "notes.md", None)
+            .await
+            .unwrap();
+        assert_eq!(versions.len(), 2);
+        assert_eq!(Some(&versions[0].commit), last.as_ref());
+        assert_eq!(versions[1].session_id.as_deref(), Some("ses_1"));
+        let selector = FileVersionSelector::Version(versions[1].id.clone());
+        let (_, content) = store
+            .read(workspace.path(), "notes.md", &selector)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(content, b"2\n");
+    }
+
+    #[test]
+    fn prune_waits_for_enough_due_snapshots() {
+        let day = 24 * 60 * 60;
+        let now = 100 * day;
+        let recent: Vec<i64> = (0..110).map(|n| now - n).collect();
+        assert_eq!(snapshots_to_keep(&recent[..105], 100, None, now), None);
+        assert_eq!(snapshots_to_keep(&recent, 100, None, now), Some(100));
+
+        let retention = Some(Duration::from_secs(30 * day as u64));
+        // Expired, but still within the grace period.
+        assert_eq!(
+            snapshots_to_keep(&[now, now - 31 * day], 100, retention, now),
+            None
+        );
+        assert_eq!(
+            snapshots_to_keep(&[now, now - 32 * day], 100, retention, now),
+            Some(1)
+        );
+        // The newest snapshot is always kept.
+        assert_eq!(
+            snapshots_to_keep(&[now - 40 * day], 100, retention, now),
+            None
+        );
+    }
+
+    #[test]
+    fn select_by_time_and_prefix() {
+        let version = |commit: &str, timestamp| FileVersion {
+            id: format!("snapshot:{commit}"),
+            source: FileVersionSource::Snapshot,
+            commit: commit.to_string(),
+            timestamp,
+            label: String::new(),
+            session_id: None,
+            deleted: false,
+        };
+        let versions = [version("bbbbbbb2", 2_000), version("bbbbbbb1", 1_000)];
+        let at = |ms| select(&versions, &FileVersionSelector::At(ms)).map(|v| v.commit.as_str());
+        assert_eq!(at(5_000), Some("bbbbbbb2"));
+        assert_eq!(at(1_500), Some("bbbbbbb1"));
+        assert_eq!(at(999), None);
+        let by = |id: &str| {
+            select(&versions, &FileVersionSelector::Version(id.to_string()))
+                .map(|v| v.commit.as_str())
+        };
+        assert_eq!(by("snapshot:bbbbbbb1"), Some("bbbbbbb1"));
+        assert_eq!(by("bbbbbbb"), None);
+        assert_eq!(by("bbbbbbb2"), Some("bbbbbbb2"));
+    }
+}
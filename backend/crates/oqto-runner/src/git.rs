@@ -0,0 +1,594 @@
+//! Git operations on a workspace's own repository.
+//!
+//! Everything shells out to `git` as the runner's user, so the user's git
+//! config (identity, hooks, SSH keys, credential helpers) applies. Pushes
+//! can additionally be given HTTPS credentials stored by oqto; they reach
+//! git through a one-off credential helper reading them from the
+//! environment, never through the command line.
+
+use std::path::Path;
+use std::process::Output;
+use std::time::Duration;
+
+use anyhow::{Context, Result, bail};
+use tokio::process::Command;
+
+use crate::file_history::{checked, in_git_repository, relative_path, workspace_git};
+use crate::protocol::{
+    GitBranch, GitBranchesResponse, GitChangeKind, GitCommitInfo, GitCredential, GitDiffResponse,
+    GitPushResponse, GitStatusEntry, GitStatusResponse,
+};
+
+/// Commits listed when the request sets no limit.
+pub const DEFAULT_LOG_LIMIT: usize = 50;
+
+/// Upper bound for a requested limit.
+const MAX_LOG_LIMIT: usize = 1000;
+
+/// Diffs are cut off at this size.
+const MAX_DIFF_BYTES: usize = 2 * 1024 * 1024;
+
+/// Pushes taking longer are aborted.
+const PUSH_TIMEOUT: Duration = Duration::from_secs(120);
+
+/// Credential helper answering with `OQTO_GIT_USERNAME`/`OQTO_GIT_PASSWORD`.
+const ENV_CREDENTIAL_HELPER: &str = "!f() { test \"$1\" = get || exit 0; \
+     echo \"username=$OQTO_GIT_USERNAME\"; echo \"password=$OQTO_GIT_PASSWORD\"; }; f";
+
+/// The workspace is not inside a git repository.
+#[derive(Debug)]
+pub struct NotARepository;
+
+impl std::fmt::Display for NotARepository {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("not a git repository")
+    }
+}
+
+impl std::error::Error for NotARepository {}
+
+async fn ensure_repository(workspace: &Path) -> Result<()> {
+    if !in_git_repository(workspace).await {
+        return Err(NotARepository.into());
+    }
+    Ok(())
+}
+
+async fn has_head(workspace: &Path) -> Result<bool> {
+    Ok(
+        workspace_git(workspace, &["rev-parse", "-q", "--verify", "HEAD"])
+            .await?
+            .status
+            .success(),
+    )
+}
+
+/// Reject revisions that git would read as options.
+fn revision(rev: &str) -> Result<&str> {
+    let rev = rev.trim();
+    if rev.is_empty() || rev.starts_with('-') {
+        bail!("invalid revision {rev:?}");
+    }
+    Ok(rev)
+}
+
+/// Branch and changed files of the repository.
+pub async fn status(workspace: &Path) -> Result<GitStatusResponse> {
+    ensure_repository(workspace).await?;
+    let output = workspace_git(
+        workspace,
+        &[
+            "status",
+            "--porcelain=v2",
+            "--branch",
+            "-z",
+            "--untracked-files=all",
+        ],
+    )
+    .await?;
+    Ok(parse_status(&checked(output, "git status")?))
+}
+
+fn change(code: u8) -> Option<GitChangeKind> {
+    match code {
+        b'A' => Some(GitChangeKind::Added),
+        b'M' => Some(GitChangeKind::Modified),
+        b'D' => Some(GitChangeKind::Deleted),
+        b'R' => Some(GitChangeKind::Renamed),
+        b'C' => Some(GitChangeKind::Copied),
+        b'T' => Some(GitChangeKind::TypeChanged),
+        _ => None,
+    }
+}
+
+/// Parse `git status --porcelain=v2 --branch -z`.
+fn parse_status(output: &str) -> GitStatusResponse {
+    let mut status = GitStatusResponse::default();
+    let mut records = output.split('\0');
+    while let Some(record) = records.next() {
+        if let Some(header) = record.strip_prefix("# ") {
+            let (key, value) = header.split_once(' ').unwrap_or((header, ""));
+            match key {
+                "branch.oid" if value != "(initial)" => status.head = Some(value.to_string()),
+                "branch.head" if value != "(detached)" => status.branch = Some(value.to_string()),
+                "branch.upstream" => status.upstream = Some(value.to_string()),
+                "branch.ab" => {
+                    for count in value.split(' ') {
+                        if let Some(n) = count.strip_prefix('+') {
+                            status.ahead = n.parse().unwrap_or(0);
+                        } else if let Some(n) = count.strip_prefix('-') {
+                            status.behind = n.parse().unwrap_or(0);
+                        }
+                    }
+                }
+                _ => {}
+            }
+            continue;
+        }
+
+        let kind = record.as_bytes().first().copied();
+        let entry = match kind {
+            // `1 XY sub mH mI mW hH hI path`, `2 ... Xscore path` + orig path
+            Some(b'1' | b'2') => {
+                let fields = if kind == Some(b'1') { 9 } else { 10 };
+                let parts: Vec<&str> = record.splitn(fields, ' ').collect();
+                let (Some(xy), Some(path)) = (parts.get(1), parts.get(fields - 1)) else {
+                    continue;
+                };
+                let xy = xy.as_bytes();
+                let orig_path = if kind == Some(b'2') {
+                    records.next().map(str::to_string)
+                } else {
+                    None
+                };
+                GitStatusEntry {
+                    path: path.to_string(),
+                    orig_path,
+                    staged: xy.first().copied().and_then(change),
+                    unstaged: xy.get(1).copied().and_then(change),
+                }
+            }
+            // `u XY sub m1 m2 m3 mW h1 h2 h3 path`
+            Some(b'u') => {
+                let Some(path) = record.splitn(11, ' ').nth(10) else {
+                    continue;
+                };
+                GitStatusEntry {
+                    path: path.to_string(),
+                    orig_path: None,
+                    staged: Some(GitChangeKind::Conflicted),
+                    unstaged: Some(GitChangeKind::Conflicted),
+                }
+            }
+            Some(b'?') => GitStatusEntry {
+                path: record[2..].to_string(),
+                orig_path: None,
+                staged: None,
+                unstaged: Some(GitChangeKind::Untracked),
+            },
+            _ => continue,
+        };
+        status.entries.push(entry);
+    }
+    status
+}
+
+/// Diff of the uncommitted changes against HEAD (only staged ones with
+/// `staged`), or of the changes made by `commit`.
+pub async fn diff(
+    workspace: &Path,
+    path: Option<&str>,
+    staged: bool,
+    commit: Option<&str>,
+) -> Result<GitDiffResponse> {
+    ensure_repository(workspace).await?;
+    let mut args = vec!["-c", "core.quotepath=off"];
+    match commit {
+        Some(commit) => args.extend(["show", "--format=", "--no-color", revision(commit)?]),
+        None => {
+            args.extend(["diff", "--no-color"]);
+            if staged {
+                args.push("--cached");
+            } else if has_head(workspace).await? {
+                args.push("HEAD");
+            }
+        }
+    }
+    let path = path.map(relative_path).transpose()?;
+    let path = path.as_deref().map(|p| p.to_string_lossy());
+    if let Some(path) = &path {
+        args.extend(["--", path.as_ref()]);
+    }
+
+    let mut diff = checked(workspace_git(workspace, &args).await?, "git diff")?;
+    let truncated = diff.len() > MAX_DIFF_BYTES;
+    if truncated {
+        let mut end = MAX_DIFF_BYTES;
+        while !diff.is_char_boundary(end) {
+            end -= 1;
+        }
+        diff.truncate(end);
+    }
+    Ok(GitDiffResponse { diff, truncated })
+}
+
+/// Commits reachable from `rev` (default HEAD), newest first.
+pub async fn log(
+    workspace: &Path,
+    limit: Option<usize>,
+    path: Option<&str>,
+    rev: Option<&str>,
+) -> Result<Vec<GitCommitInfo>> {
+    ensure_repository(workspace).await?;
+    if rev.is_none() && !has_head(workspace).await? {
+        return Ok(Vec::new());
+    }
+    let limit = limit
+        .unwrap_or(DEFAULT_LOG_LIMIT)
+        .clamp(1, MAX_LOG_LIMIT)
+        .to_string();
+    let mut args = vec![
+        "log",
+        "-z",
+        "-n",
+        &limit,
+        "--format=%H%x1f%an%x1f%ae%x1f%at%x1f%s",
+    ];
+    if let Some(rev) = rev {
+        args.push(revision(rev)?);
+    }
+    let path = path.map(relative_path).transpose()?;
+    let path = path.as_deref().map(|p| p.to_string_lossy());
+    if let Some(path) = &path {
+        args.extend(["--", path.as_ref()]);
+    }
+    let output = checked(workspace_git(workspace, &args).await?, "git log")?;
+    Ok(output.split('\0').filter_map(parse_commit).collect())
+}
+
+fn parse_commit(record: &str) -> Option<GitCommitInfo> {
+    let mut fields = record.trim_start_matches('\n').splitn(5, '\x1f');
+    let commit = fields.next().filter(|c| !c.is_empty())?.to_string();
+    let author_name = fields.next()?.to_string();
+    let author_email = fields.next()?.to_string();
+    let timestamp = fields.next()?.parse::<i64>().ok()? * 1000;
+    let subject = fields.next().unwrap_or_default().to_string();
+    Some(GitCommitInfo {
+        commit,
+        author_name,
+        author_email,
+        timestamp,
+        subject,
+    })
+}
+
+/// Local branches.
+pub async fn branches(workspace: &Path) -> Result<GitBranchesResponse> {
+    ensure_repository(workspace).await?;
+    let output = workspace_git(
+        workspace,
+        &[
+            "for-each-ref",
+            "--format=%(refname:short)%1f%(objectname)%1f%(upstream:short)%1f%(HEAD)",
+            "refs/heads",
+        ],
+    )
+    .await?;
+    let branches: Vec<GitBranch> = checked(output, "git for-each-ref")?
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.split('\x1f');
+            let name = fields.next().filter(|n| !n.is_empty())?.to_string();
+            let commit = fields.next()?.to_string();
+            let upstream = fields.next().filter(|u| !u.is_empty()).map(str::to_string);
+            let current = fields.next() == Some("*");
+            Some(GitBranch {
+                name,
+                commit,
+                upstream,
+                current,
+            })
+        })
+        .collect();
+    let current = match branches.iter().find(|b| b.current) {
+        Some(branch) => Some(branch.name.clone()),
+        // A new repository has no branch refs until its first commit.
+        None => checked(
+            workspace_git(workspace, &["symbolic-ref", "-q", "--short", "HEAD"]).await?,
+            "git symbolic-ref",
+        )
+        .ok()
+        .map(|name| name.trim().to_string())
+        .filter(|name| !name.is_empty()),
+    };
+    Ok(GitBranchesResponse { current, branches })
+}
+
+/// Check out branch `name`, creating it from HEAD with `create`.
+pub async fn switch_branch(workspace: &Path, name: &str, create: bool) -> Result<()> {
+    ensure_repository(workspace).await?;
+    let valid = !name.starts_with('-')
+        && workspace_git(workspace, &["check-ref-format", "--branch", name])
+            .await?
+            .status
+            .success();
+    if !valid {
+        bail!("invalid branch name {name:?}");
+    }
+    let args = if create {
+        vec!["switch", "-c", name]
+    } else {
+        vec!["switch", name]
+    };
+    checked(workspace_git(workspace, &args).await?, "git switch")?;
+    Ok(())
+}
+
+/// Stage `paths` (every change when None) and commit them.
+pub async fn commit(
+    workspace: &Path,
+    message: &str,
+    paths: Option<&[String]>,
+    author: Option<(&str, &str)>,
+) -> Result<GitCommitInfo> {
+    ensure_repository(workspace).await?;
+    if message.trim().is_empty() {
+        bail!("commit message must not be empty");
+    }
+    let paths = paths
+        .map(|paths| {
+            paths
+                .iter()
+                .map(|p| relative_path(p).map(|p| p.to_string_lossy().into_owned()))
+                .collect::<Result<Vec<_>>>()
+        })
+        .transpose()?;
+    if paths.as_ref().is_some_and(Vec::is_empty) {
+        bail!("no paths to commit");
+    }
+
+    let mut add_args = vec!["add", "-A", "--"];
+    let mut commit_args = vec!["commit", "-q", "-m", message];
+    if let Some(paths) = &paths {
+        add_args.extend(paths.iter().map(String::as_str));
+        commit_args.push("--");
+        commit_args.extend(paths.iter().map(String::as_str));
+    }
+    checked(workspace_git(workspace, &add_args).await?, "git add")?;
+    let staged = workspace_git(workspace, &["diff", "--cached", "--quiet"]).await?;
+    if staged.status.success() {
+        bail!("nothing to commit");
+    }
+
+    let mut command = git_command(workspace, &commit_args);
+    if let Some((name, email)) = author {
+        command
+            .env("GIT_AUTHOR_NAME", name)
+            .env("GIT_AUTHOR_EMAIL", email)
+            .env("GIT_COMMITTER_NAME", name)
+            .env("GIT_COMMITTER_EMAIL", email);
+    }
+    checked(command.output().await.context("running git")?, "git commit")?;
+
+    log(workspace, Some(1), None, None)
+        .await?
+        .into_iter()
+        .next()
+        .context("commit not found after committing")
+}
+
+/// Push `branch` (default: the current one) to `remote` (default: its
+/// upstream remote, then `origin`).
+pub async fn push(
+    workspace: &Path,
+    remote: Option<&str>,
+    branch: Option<&str>,
+    set_upstream: bool,
+    credentials: &[GitCredential],
+) -> Result<GitPushResponse> {
+    ensure_repository(workspace).await?;
+    let branch = match branch {
+        Some(branch) => revision(branch)?.to_string(),
+        None => checked(
+            workspace_git(workspace, &["symbolic-ref", "-q", "--short", "HEAD"]).await?,
+            "git symbolic-ref",
+        )
+        .map_err(|_| anyhow::anyhow!("HEAD is detached; name the branch to push"))?
+        .trim()
+        .to_string(),
+    };
+    let remote = match remote {
+        Some(remote) => revision(remote)?.to_string(),
+        None => {
+            let key = format!("branch.{branch}.remote");
+            let configured = workspace_git(workspace, &["config", "--get", &key]).await?;
+            match checked(configured, "git config") {
+                Ok(remote) if !remote.trim().is_empty() => remote.trim().to_string(),
+                _ => "origin".to_string(),
+            }
+        }
+    };
+    let url = checked(
+        workspace_git(workspace, &["remote", "get-url", &remote]).await?,
+        "git remote get-url",
+    )?;
+
+    let mut args = vec!["push", "--porcelain"];
+    if set_upstream {
+        args.push("--set-upstream");
+    }
+    args.extend([remote.as_str(), branch.as_str()]);
+    let mut command = match credential_for(url.trim(), credentials) {
+        Some(credential) => {
+            let mut with_helper = vec!["-c", "credential.helper=", "-c"];
+            let helper = format!("credential.helper={ENV_CREDENTIAL_HELPER}");
+            with_helper.push(&helper);
+            with_helper.extend(&args);
+            let mut command = git_command(workspace, &with_helper);
+            command
+                .env("OQTO_GIT_USERNAME", &credential.username)
+                .env("OQTO_GIT_PASSWORD", &credential.password);
+            command
+        }
+        None => git_command(workspace, &args),
+    };
+    command.env("GIT_TERMINAL_PROMPT", "0").kill_on_drop(true);
+
+    let output: Output = tokio::time::timeout(PUSH_TIMEOUT, command.output())
+        .await
+        .map_err(|_| anyhow::anyhow!("git push timed out"))?
+        .context("running git")?;
+    let stderr = String::from_utf8_lossy(&output.stderr).into_owned();
+    let stdout = checked(output, "git push")?;
+    Ok(GitPushResponse {
+        remote,
+        branch,
+        output: format!("{}{}", stderr, stdout).trim().to_string(),
+    })
+}
+
+/// Host (with port) of an HTTP(S) remote URL.
+fn url_host(url: &str) -> Option<&str> {
+    let rest = url
+        .strip_prefix("https://")
+        .or_else(|| url.strip_prefix("http://"))?;
+    let authority = rest.split('/').next()?;
+    let host = authority.rsplit_once('@').map_or(authority, |(_, h)| h);
+    (!host.is_empty()).then_some(host)
+}
+
+fn credential_for<'a>(url: &str, credentials: &'a [GitCredential]) -> Option<&'a GitCredential> {
+    let host = url_host(url)?;
+    credentials
+        .iter()
+        .find(|c| c.host.eq_ignore_ascii_case(host))
+}
+
+fn git_command(workspace: &Path, args: &[&str]) -> Command {
+    let mut command = Command::new("git");
+    command
+        .args(args)
+        .current_dir(workspace)
+        .env_remove("GIT_DIR")
+        .env_remove("GIT_WORK_TREE")
+        .env_remove("GIT_INDEX_FILE");
+    command
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn git_available() -> bool {
+        std::process::Command::new("git")
+            .arg("--version")
+            .output()
+            .is_ok_and(|o| o.status.success())
+    }
+
+    #[test]
+    fn parses_porcelain_status() {
+        let output = "# branch.oid 1234abcd\0# branch.head main\0\
+            # branch.upstream origin/main\0# branch.ab +2 -1\0\
+            1 .M N... 100644 100644 100644 aaa bbb src/main.rs\0\
+            2 R. N... 100644 100644 100644 aaa bbb R100 new name.rs\0old.rs\0\
+            u UU N... 100644 100644 100644 100644 a b c conflict.txt\0\
+            ? notes/todo.md\0";
+        let status = parse_status(output);
+        assert_eq!(status.branch.as_deref(), Some("main"));
+        assert_eq!(status.head.as_deref(), Some("1234abcd"));
+        assert_eq!(status.upstream.as_deref(), Some("origin/main"));
+        assert_eq!((status.ahead, status.behind), (2, 1));
+        assert_eq!(status.entries.len(), 4);
+        assert_eq!(status.entries[0].path, "src/main.rs");
+        assert_eq!(status.entries[0].staged, None);
+        assert_eq!(status.entries[0].unstaged, Some(GitChangeKind::Modified));
+        assert_eq!(status.entries[1].path, "new name.rs");
+        assert_eq!(status.entries[1].orig_path.as_deref(), Some("old.rs"));
+        assert_eq!(status.entries[1].staged, Some(GitChangeKind::Renamed));
+        assert_eq!(status.entries[2].staged, Some(GitChangeKind::Conflicted));
+        assert_eq!(status.entries[3].unstaged, Some(GitChangeKind::Untracked));
+
+        let detached = parse_status("# branch.oid (initial)\0# branch.head (detached)\0");
+        assert_eq!(detached.branch, None);
+        assert_eq!(detached.head, None);
+    }
+
+    #[test]
+    fn matches_credentials_by_host() {
+        let credential = |host: &str| GitCredential {
+            host: host.to_string(),
+            username: "me".to_string(),
+            password: "secret".to_string(),
+        };
+        let credentials = [credential("github.com"), credential("git.example.com:8443")];
+        let host = |url| credential_for(url, &credentials).map(|c| c.host.as_str());
+        assert_eq!(host("https://github.com/o/r.git"), Some("github.com"));
+        assert_eq!(host("https://me@GitHub.com/o/r"), Some("github.com"));
+        assert_eq!(
+            host("https://git.example.com:8443/r"),
+            Some("git.example.com:8443")
+        );
+        assert_eq!(host("git@github.com:o/r.git"), None);
+        assert!(!format!("{:?}", credentials[0]).contains("secret"));
+    }
+
+    #[tokio::test]
+    async fn commits_and_reports_changes() {
+        if !git_available() {
+            return;
+        }
+        let workspace = tempfile::tempdir().unwrap();
+        let root = workspace.path();
+        let git = |args: &[&str]| {
+            std::process::Command::new("git")
+                .args(args)
+                .current_dir(root)
+                .output()
+                .unwrap()
+        };
+        git(&["init", "-q", "-b", "main"]);
+
+        assert!(log(root, None, None, None).await.unwrap().is_empty());
+        std::fs::write(root.join("a.txt"), "one\n").unwrap();
+        let untracked = status(root).await.unwrap();
+        assert_eq!(untracked.entries.len(), 1);
+        assert_eq!(
+            untracked.entries[0].unstaged,
+            Some(GitChangeKind::Untracked)
+        );
+
+        let author = Some(("Ada", "ada@example.com"));
+        let first = commit(root, "Add a", None, author).await.unwrap();
+        assert_eq!(first.subject, "Add a");
+        assert_eq!(first.author_email, "ada@example.com");
+        assert!(commit(root, "Again", None, author).await.is_err());
+
+        std::fs::write(root.join("a.txt"), "two\n").unwrap();
+        let changes = diff(root, None, false, None).await.unwrap();
+        assert!(changes.diff.contains("+two"));
+        assert!(!changes.truncated);
+        assert!(diff(root, Some("../x"), false, None).await.is_err());
+
+        switch_branch(root, "feature", true).await.unwrap();
+        commit(root, "Change a", Some(&["a.txt".to_string()]), author)
+            .await
+            .unwrap();
+        let listed = branches(root).await.unwrap();
+        assert_eq!(listed.current.as_deref(), Some("feature"));
+        assert_eq!(listed.branches.len(), 2);
+        assert!(switch_branch(root, "-bad", false).await.is_err());
+
+        let commits = log(root, None, Some("a.txt"), None).await.unwrap();
+        assert_eq!(commits.len(), 2);
+        assert_eq!(commits[0].subject, "Change a");
+        let shown = diff(root, None, false, Some(&commits[1].commit))
+            .await
+            .unwrap();
+        assert!(shown.diff.contains("+one"));
+
+        let outside = tempfile::tempdir().unwrap();
+        let err = status(outside.path()).await.unwrap_err();
+        assert!(err.downcast_ref::<NotARepository>().is_some());
+    }
+}
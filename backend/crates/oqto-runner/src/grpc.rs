@@ -0,0 +1,709 @@
+//! gRPC transport for the runner protocol.
+//!
+//! The same [`RunnerRequest`] and [`RunnerResponse`] messages as the
+//! newline-delimited JSON protocol, carried as JSON-encoded gRPC messages
+//! of the `oqto.runner.v1.Runner` service:
+//!
+//! - `Call`: one request, one response (unary).
+//! - `Subscribe`: `pi_subscribe` or `subscribe_stdout`, answered with a
+//!   stream that ends with the subscription.
+//!
+//! Over gRPC, requests carry deadlines (`grpc-timeout`), many calls share
+//! one HTTP/2 connection, and remote runners can be reached over TCP with
+//! TLS and client certificates. The runner serves gRPC on its Unix socket,
+//! telling it from JSON by the HTTP/2 connection preface, and with
+//! `--grpc-listen` on a TCP port, where clients also present the runner
+//! token as `authorization: Bearer <token>`.
+//!
+//! The runner answers each gRPC call by opening an in-process connection
+//! to its JSON request loop, so both transports behave the same.
+
+use std::collections::HashMap;
+use std::marker::PhantomData;
+use std::path::{Path, PathBuf};
+use std::pin::Pin;
+use std::sync::{Arc, LazyLock, Mutex};
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+use anyhow::{Context as _, Result};
+use bytes::{Buf, BufMut};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use tokio::io::{
+    AsyncBufReadExt, AsyncRead, AsyncWrite, AsyncWriteExt, BufReader, DuplexStream, ReadBuf,
+};
+use tokio::net::UnixStream;
+use tokio::net::unix::{OwnedReadHalf, OwnedWriteHalf};
+use tonic::body::Body;
+use tonic::codec::{Codec, DecodeBuf, Decoder, EncodeBuf, Encoder, Streaming};
+use tonic::codegen::{BoxFuture, Service, http};
+use tonic::server::{NamedService, ServerStreamingService, UnaryService};
+use tonic::transport::server::Connected;
+use tonic::transport::{
+    Certificate, Channel, ClientTlsConfig, Endpoint, Identity, ServerTlsConfig,
+};
+use tonic::{Request, Response, Status};
+
+use crate::protocol::{RunnerRequest, RunnerResponse};
+
+/// Name of the gRPC service.
+pub const SERVICE: &str = "oqto.runner.v1.Runner";
+const CALL_PATH: &str = "/oqto.runner.v1.Runner/Call";
+const SUBSCRIBE_PATH: &str = "/oqto.runner.v1.Runner/Subscribe";
+
+/// Largest message either side accepts. File reads and chat histories can
+/// be far over tonic's 4 MiB default.
+const MAX_MESSAGE_SIZE: usize = 256 * 1024 * 1024;
+
+/// Buffer of the in-process connections calls are answered through.
+const CONNECTION_BUFFER: usize = 64 * 1024;
+
+type ResponseStream = Pin<Box<dyn futures::Stream<Item = Result<RunnerResponse, Status>> + Send>>;
+
+// ============================================================================
+// Codec
+// ============================================================================
+
+/// Encodes `E` and decodes `D` as JSON, so gRPC carries the protocol's own
+/// serde types.
+pub struct JsonCodec<E, D>(PhantomData<fn(E) -> D>);
+
+impl<E, D> Default for JsonCodec<E, D> {
+    fn default() -> Self {
+        Self(PhantomData)
+    }
+}
+
+pub struct JsonEncoder<T>(PhantomData<fn(T)>);
+
+pub struct JsonDecoder<T>(PhantomData<fn() -> T>);
+
+impl<E, D> Codec for JsonCodec<E, D>
+where
+    E: Serialize + Send + 'static,
+    D: DeserializeOwned + Send + 'static,
+{
+    type Encode = E;
+    type Decode = D;
+    type Encoder = JsonEncoder<E>;
+    type Decoder = JsonDecoder<D>;
+
+    fn encoder(&mut self) -> Self::Encoder {
+        JsonEncoder(PhantomData)
+    }
+
+    fn decoder(&mut self) -> Self::Decoder {
+        JsonDecoder(PhantomData)
+    }
+}
+
+impl<T: Serialize> Encoder for JsonEncoder<T> {
+    type Item = T;
+    type Error = Status;
+
+    fn encode(&mut self, item: T, dst: &mut EncodeBuf<'_>) -> Result<(), Status> {
+        serde_json::to_writer(dst.writer(), &item)
+            .map_err(|e| Status::internal(format!("encoding message: {e}")))
+    }
+}
+
+impl<T: DeserializeOwned> Decoder for JsonDecoder<T> {
+    type Item = T;
+    type Error = Status;
+
+    fn decode(&mut self, src: &mut DecodeBuf<'_>) -> Result<Option<T>, Status> {
+        serde_json::from_reader(src.reader())
+            .map(Some)
+            .map_err(|e| Status::invalid_argument(format!("decoding message: {e}")))
+    }
+}
+
+// ============================================================================
+// Client
+// ============================================================================
+
+/// TLS for gRPC connections to remote runners, used when `ca_cert` is set.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct GrpcTlsConfig {
+    /// CA certificate (PEM) runner certificates are checked against.
+    pub ca_cert: Option<PathBuf>,
+    /// Client certificate (PEM) presented to runners that require one.
+    pub client_cert: Option<PathBuf>,
+    /// Key of `client_cert` (PEM).
+    pub client_key: Option<PathBuf>,
+    /// Name expected in runner certificates, when it is not the host in
+    /// the runner's address.
+    pub domain: Option<String>,
+}
+
+impl GrpcTlsConfig {
+    pub fn enabled(&self) -> bool {
+        self.ca_cert.is_some()
+    }
+
+    fn client_config(&self) -> Result<ClientTlsConfig> {
+        let mut config = ClientTlsConfig::new();
+        if let Some(ca) = &self.ca_cert {
+            config = config.ca_certificate(Certificate::from_pem(read_pem(ca)?));
+        }
+        match (&self.client_cert, &self.client_key) {
+            (Some(cert), Some(key)) => {
+                config = config.identity(Identity::from_pem(read_pem(cert)?, read_pem(key)?));
+            }
+            (None, None) => {}
+            _ => anyhow::bail!("client_cert and client_key must be set together"),
+        }
+        if let Some(domain) = &self.domain {
+            config = config.domain_name(domain.clone());
+        }
+        Ok(config)
+    }
+}
+
+fn read_pem(path: &Path) -> Result<Vec<u8>> {
+    std::fs::read(path).with_context(|| format!("reading {}", path.display()))
+}
+
+/// Channels by endpoint, so every client of a runner shares one HTTP/2
+/// connection.
+static CHANNELS: LazyLock<Mutex<HashMap<String, Channel>>> =
+    LazyLock::new(|| Mutex::new(HashMap::new()));
+
+fn cached_channel(key: String, connect: impl FnOnce() -> Result<Channel>) -> Result<Channel> {
+    let mut channels = CHANNELS.lock().unwrap_or_else(|e| e.into_inner());
+    if let Some(channel) = channels.get(&key) {
+        return Ok(channel.clone());
+    }
+    let channel = connect()?;
+    channels.insert(key, channel.clone());
+    Ok(channel)
+}
+
+/// A gRPC connection to one runner.
+#[derive(Clone)]
+pub struct GrpcClient {
+    channel: Channel,
+    token: Option<String>,
+}
+
+impl GrpcClient {
+    /// A client for the runner listening on a Unix socket. Connects on
+    /// first use.
+    pub fn unix(socket_path: &Path) -> Result<Self> {
+        let path = socket_path.to_path_buf();
+        let channel = cached_channel(format!("unix:{}", path.display()), || {
+            Ok(
+                Endpoint::from_static("http://[::]:0").connect_with_connector_lazy(
+                    tower::service_fn(move |_: http::Uri| {
+                        let path = path.clone();
+                        async move {
+                            let stream = UnixStream::connect(path).await?;
+                            Ok::<_, std::io::Error>(hyper_util::rt::TokioIo::new(stream))
+                        }
+                    }),
+                ),
+            )
+        })?;
+        Ok(Self {
+            channel,
+            token: None,
+        })
+    }
+
+    /// A client for a remote runner started with `--grpc-listen`, at
+    /// `address` (`host:port`). Connects on first use.
+    pub fn tcp(address: &str, token: &str, tls: &GrpcTlsConfig) -> Result<Self> {
+        let channel = cached_channel(format!("tcp:{address}"), || {
+            if tls.client_cert.is_some() && !tls.enabled() {
+                anyhow::bail!("a client certificate needs ca_cert to be set");
+            }
+            let scheme = if tls.enabled() { "https" } else { "http" };
+            let mut endpoint = Endpoint::from_shared(format!("{scheme}://{address}"))
+                .with_context(|| format!("invalid runner address {address}"))?
+                .connect_timeout(Duration::from_secs(10))
+                .http2_keep_alive_interval(Duration::from_secs(30))
+                .keep_alive_while_idle(true);
+            if tls.enabled() {
+                endpoint = endpoint
+                    .tls_config(tls.client_config()?)
+                    .context("configuring runner TLS")?;
+            }
+            Ok(endpoint.connect_lazy())
+        })?;
+        Ok(Self {
+            channel,
+            token: Some(token.to_string()),
+        })
+    }
+
+    fn request(&self, message: RunnerRequest) -> Result<Request<RunnerRequest>, Status> {
+        let mut request = Request::new(message);
+        if let Some(token) = &self.token {
+            let value = format!("Bearer {token}")
+                .parse()
+                .map_err(|_| Status::invalid_argument("runner token is not ASCII"))?;
+            request.metadata_mut().insert("authorization", value);
+        }
+        Ok(request)
+    }
+
+    async fn grpc(&self) -> Result<tonic::client::Grpc<Channel>, Status> {
+        let mut grpc = tonic::client::Grpc::new(self.channel.clone())
+            .max_decoding_message_size(MAX_MESSAGE_SIZE)
+            .max_encoding_message_size(MAX_MESSAGE_SIZE);
+        grpc.ready()
+            .await
+            .map_err(|e| Status::unavailable(format!("runner not ready: {e}")))?;
+        Ok(grpc)
+    }
+
+    /// Send a request that must be answered within `timeout`.
+    pub async fn call(
+        &self,
+        message: RunnerRequest,
+        timeout: Duration,
+    ) -> Result<RunnerResponse, Status> {
+        let mut request = self.request(message)?;
+        request.set_timeout(timeout);
+        let response = self
+            .grpc()
+            .await?
+            .unary(
+                request,
+                http::uri::PathAndQuery::from_static(CALL_PATH),
+                JsonCodec::default(),
+            )
+            .await?;
+        Ok(response.into_inner())
+    }
+
+    /// Start a subscription. The stream yields the confirmation first.
+    pub async fn subscribe(
+        &self,
+        message: RunnerRequest,
+    ) -> Result<Streaming<RunnerResponse>, Status> {
+        let request = self.request(message)?;
+        let response = self
+            .grpc()
+            .await?
+            .server_streaming(
+                request,
+                http::uri::PathAndQuery::from_static(SUBSCRIBE_PATH),
+                JsonCodec::default(),
+            )
+            .await?;
+        Ok(response.into_inner())
+    }
+}
+
+// ============================================================================
+// Server
+// ============================================================================
+
+/// Opens an in-process connection to the runner's JSON request loop.
+pub type OpenConnection = Arc<dyn Fn() -> DuplexStream + Send + Sync>;
+
+/// A pair of in-process streams: the caller's end, and the end to serve
+/// the connection on.
+pub fn connection_pair() -> (DuplexStream, DuplexStream) {
+    tokio::io::duplex(CONNECTION_BUFFER)
+}
+
+/// The `oqto.runner.v1.Runner` service.
+#[derive(Clone)]
+pub struct RunnerGrpcService {
+    open: OpenConnection,
+    /// Token TCP clients must present; None on the Unix socket, whose
+    /// permissions already decide who may connect.
+    token: Option<Arc<str>>,
+}
+
+impl RunnerGrpcService {
+    pub fn new(open: OpenConnection) -> Self {
+        Self { open, token: None }
+    }
+
+    pub fn with_token(mut self, token: &str) -> Self {
+        self.token = Some(Arc::from(token));
+        self
+    }
+
+    fn authorized(&self, headers: &http::HeaderMap) -> bool {
+        let Some(token) = &self.token else {
+            return true;
+        };
+        headers
+            .get("authorization")
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.strip_prefix("Bearer "))
+            .is_some_and(|given| crate::remote::token_matches(given, token))
+    }
+}
+
+impl NamedService for RunnerGrpcService {
+    const NAME: &'static str = SERVICE;
+}
+
+impl Service<http::Request<Body>> for RunnerGrpcService {
+    type Response = http::Response<Body>;
+    type Error = std::convert::Infallible;
+    type Future = BoxFuture<Self::Response, Self::Error>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, req: http::Request<Body>) -> Self::Future {
+        if !self.authorized(req.headers()) {
+            return Box::pin(async {
+                Ok(Status::unauthenticated("invalid runner token").into_http())
+            });
+        }
+        let open = Arc::clone(&self.open);
+        match req.uri().path() {
+            CALL_PATH => Box::pin(async move {
+                let mut grpc = tonic::server::Grpc::new(JsonCodec::default())
+                    .max_decoding_message_size(MAX_MESSAGE_SIZE)
+                    .max_encoding_message_size(MAX_MESSAGE_SIZE);
+                Ok(grpc.unary(CallMethod(open), req).await)
+            }),
+            SUBSCRIBE_PATH => Box::pin(async move {
+                let mut grpc = tonic::server::Grpc::new(JsonCodec::default())
+                    .max_decoding_message_size(MAX_MESSAGE_SIZE)
+                    .max_encoding_message_size(MAX_MESSAGE_SIZE);
+                Ok(grpc.server_streaming(SubscribeMethod(open), req).await)
+            }),
+            path => {
+                let status = Status::unimplemented(format!("unknown method {path}"));
+                Box::pin(async move { Ok(status.into_http()) })
+            }
+        }
+    }
+}
+
+fn is_subscription(request: &RunnerRequest) -> bool {
+    matches!(
+        request,
+        RunnerRequest::PiSubscribe(_) | RunnerRequest::SubscribeStdout(_)
+    )
+}
+
+/// Write `request` to a fresh in-process connection and return its lines.
+async fn send(
+    open: &OpenConnection,
+    request: &RunnerRequest,
+) -> Result<
+    (
+        tokio::io::Lines<BufReader<tokio::io::ReadHalf<DuplexStream>>>,
+        tokio::io::WriteHalf<DuplexStream>,
+    ),
+    Status,
+> {
+    let (reader, mut writer) = tokio::io::split(open());
+    let mut line = serde_json::to_string(request)
+        .map_err(|e| Status::invalid_argument(format!("encoding request: {e}")))?;
+    line.push('\n');
+    writer
+        .write_all(line.as_bytes())
+        .await
+        .map_err(|e| Status::unavailable(format!("runner connection failed: {e}")))?;
+    Ok((BufReader::new(reader).lines(), writer))
+}
+
+fn parse_response(line: &str) -> Result<RunnerResponse, Status> {
+    serde_json::from_str(line).map_err(|e| Status::internal(format!("invalid response: {e}")))
+}
+
+struct CallMethod(OpenConnection);
+
+impl UnaryService<RunnerRequest> for CallMethod {
+    type Response = RunnerResponse;
+    type Future = BoxFuture<Response<RunnerResponse>, Status>;
+
+    fn call(&mut self, request: Request<RunnerRequest>) -> Self::Future {
+        let open = Arc::clone(&self.0);
+        Box::pin(async move {
+            let request = request.into_inner();
+            if is_subscription(&request) {
+                return Err(Status::invalid_argument("subscriptions use Subscribe"));
+            }
+            let (mut lines, _writer) = send(&open, &request).await?;
+            let line = lines
+                .next_line()
+                .await
+                .map_err(|e| Status::unavailable(format!("reading response: {e}")))?
+                .ok_or_else(|| Status::unavailable("runner closed the connection"))?;
+            parse_response(&line).map(Response::new)
+        })
+    }
+}
+
+struct SubscribeMethod(OpenConnection);
+
+impl ServerStreamingService<RunnerRequest> for SubscribeMethod {
+    type Response = RunnerResponse;
+    type ResponseStream = ResponseStream;
+    type Future = BoxFuture<Response<Self::ResponseStream>, Status>;
+
+    fn call(&mut self, request: Request<RunnerRequest>) -> Self::Future {
+        let open = Arc::clone(&self.0);
+        Box::pin(async move {
+            let request = request.into_inner();
+            if !is_subscription(&request) {
+                return Err(Status::invalid_argument(
+                    "only pi_subscribe and subscribe_stdout use Subscribe",
+                ));
+            }
+            let (lines, writer) = send(&open, &request).await?;
+            // Dropping the stream (the client went away) drops the
+            // connection, which ends the subscription in the runner.
+            let stream = futures::stream::unfold(Some((lines, writer, true)), |state| async move {
+                let (mut lines, writer, first) = state?;
+                let line = lines.next_line().await.ok().flatten()?;
+                let response = parse_response(&line);
+                let last = match &response {
+                    Ok(response) => ends_subscription(response, first),
+                    Err(_) => true,
+                };
+                let next = (!last).then_some((lines, writer, false));
+                Some((response, next))
+            });
+            Ok(Response::new(Box::pin(stream) as Self::ResponseStream))
+        })
+    }
+}
+
+/// Whether `response` is the last of a subscription. The JSON connection
+/// stays open for further requests after one, so the stream has to end by
+/// itself.
+fn ends_subscription(response: &RunnerResponse, first: bool) -> bool {
+    match response {
+        RunnerResponse::StdoutEnd(_) | RunnerResponse::PiSubscriptionEnd(_) => true,
+        // A refused subscription.
+        RunnerResponse::Error(_) => first,
+        _ => false,
+    }
+}
+
+/// Whether a connection that starts with `buf` speaks HTTP/2 (gRPC) rather
+/// than JSON lines.
+pub fn is_http2_preface(buf: &[u8]) -> bool {
+    const PREFACE: &[u8] = b"PRI * HTTP/2.0\r\n\r\nSM\r\n\r\n";
+    !buf.is_empty() && PREFACE.starts_with(&buf[..buf.len().min(PREFACE.len())])
+}
+
+/// A Unix socket connection recognised as gRPC, with the bytes read while
+/// recognising it still buffered.
+pub struct GrpcConn {
+    reader: BufReader<OwnedReadHalf>,
+    writer: OwnedWriteHalf,
+}
+
+impl GrpcConn {
+    pub fn new(reader: BufReader<OwnedReadHalf>, writer: OwnedWriteHalf) -> Self {
+        Self { reader, writer }
+    }
+}
+
+impl Connected for GrpcConn {
+    type ConnectInfo = ();
+
+    fn connect_info(&self) -> Self::ConnectInfo {}
+}
+
+impl AsyncRead for GrpcConn {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<std::io::Result<()>> {
+        Pin::new(&mut self.reader).poll_read(cx, buf)
+    }
+}
+
+impl AsyncWrite for GrpcConn {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<std::io::Result<usize>> {
+        Pin::new(&mut self.writer).poll_write(cx, buf)
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        Pin::new(&mut self.writer).poll_flush(cx)
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        Pin::new(&mut self.writer).poll_shutdown(cx)
+    }
+}
+
+/// TLS for the `--grpc-listen` port.
+#[derive(Debug, Clone)]
+pub struct ServerTls {
+    pub cert: PathBuf,
+    pub key: PathBuf,
+    /// CA client certificates must be signed by; clients without one are
+    /// refused when set.
+    pub client_ca: Option<PathBuf>,
+}
+
+impl ServerTls {
+    pub fn config(&self) -> Result<ServerTlsConfig> {
+        let mut config = ServerTlsConfig::new().identity(Identity::from_pem(
+            read_pem(&self.cert)?,
+            read_pem(&self.key)?,
+        ));
+        if let Some(ca) = &self.client_ca {
+            config = config.client_ca_root(Certificate::from_pem(read_pem(ca)?));
+        }
+        Ok(config)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protocol::{StdoutEndResponse, StdoutLineResponse, StdoutSubscribedResponse};
+    use tokio::net::UnixListener;
+    use tokio_stream::wrappers::ReceiverStream;
+
+    /// A stand-in for the runner's request loop: answers pings, and
+    /// subscriptions with two lines and an end.
+    fn fake_runner() -> OpenConnection {
+        Arc::new(|| {
+            let (client, server) = connection_pair();
+            tokio::spawn(async move {
+                let (reader, mut writer) = tokio::io::split(server);
+                let mut lines = BufReader::new(reader).lines();
+                while let Ok(Some(line)) = lines.next_line().await {
+                    let responses = match serde_json::from_str(&line).unwrap() {
+                        RunnerRequest::Ping => vec![RunnerResponse::Pong],
+                        RunnerRequest::SubscribeStdout(req) => vec![
+                            RunnerResponse::StdoutSubscribed(StdoutSubscribedResponse {
+                                id: req.id.clone(),
+                            }),
+                            RunnerResponse::StdoutLine(StdoutLineResponse {
+                                id: req.id.clone(),
+                                line: "hello".to_string(),
+                            }),
+                            RunnerResponse::StdoutEnd(StdoutEndResponse {
+                                id: req.id,
+                                exit_code: Some(0),
+                            }),
+                        ],
+                        other => panic!("unexpected request {other:?}"),
+                    };
+                    for response in responses {
+                        let mut line = serde_json::to_string(&response).unwrap();
+                        line.push('\n');
+                        writer.write_all(line.as_bytes()).await.unwrap();
+                    }
+                    // The connection stays open, as in the runner.
+                }
+            });
+            client
+        })
+    }
+
+    /// Serve `service` on a Unix socket in `dir` the way the runner does.
+    fn serve(dir: &Path, service: RunnerGrpcService) -> PathBuf {
+        let path = dir.join("runner.sock");
+        let listener = UnixListener::bind(&path).unwrap();
+        let (tx, rx) = tokio::sync::mpsc::channel::<std::io::Result<GrpcConn>>(4);
+        tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                let (reader, writer) = stream.into_split();
+                let mut reader = BufReader::new(reader);
+                assert!(is_http2_preface(reader.fill_buf().await.unwrap()));
+                tx.send(Ok(GrpcConn::new(reader, writer))).await.unwrap();
+            }
+        });
+        tokio::spawn(
+            tonic::transport::Server::builder()
+                .add_service(service)
+                .serve_with_incoming(ReceiverStream::new(rx)),
+        );
+        path
+    }
+
+    #[tokio::test]
+    async fn calls_and_subscribes_over_unix_socket() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = serve(dir.path(), RunnerGrpcService::new(fake_runner()));
+        let client = GrpcClient::unix(&path).unwrap();
+
+        let response = client
+            .call(RunnerRequest::Ping, Duration::from_secs(5))
+            .await
+            .unwrap();
+        assert!(matches!(response, RunnerResponse::Pong));
+
+        let mut stream = client
+            .subscribe(RunnerRequest::SubscribeStdout(
+                crate::protocol::SubscribeStdoutRequest {
+                    id: "p1".to_string(),
+                },
+            ))
+            .await
+            .unwrap();
+        let mut received = Vec::new();
+        while let Some(response) = stream.message().await.unwrap() {
+            received.push(response);
+        }
+        assert!(matches!(received[0], RunnerResponse::StdoutSubscribed(_)));
+        assert!(matches!(&received[1], RunnerResponse::StdoutLine(l) if l.line == "hello"));
+        assert!(matches!(received[2], RunnerResponse::StdoutEnd(_)));
+        assert_eq!(received.len(), 3);
+
+        let error = client
+            .call(
+                RunnerRequest::SubscribeStdout(crate::protocol::SubscribeStdoutRequest {
+                    id: "p1".to_string(),
+                }),
+                Duration::from_secs(5),
+            )
+            .await
+            .unwrap_err();
+        assert_eq!(error.code(), tonic::Code::InvalidArgument);
+    }
+
+    #[tokio::test]
+    async fn requires_the_token_when_configured() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = serve(
+            dir.path(),
+            RunnerGrpcService::new(fake_runner()).with_token("s3cret"),
+        );
+        let anonymous = GrpcClient::unix(&path).unwrap();
+        let error = anonymous
+            .call(RunnerRequest::Ping, Duration::from_secs(5))
+            .await
+            .unwrap_err();
+        assert_eq!(error.code(), tonic::Code::Unauthenticated);
+
+        let client = GrpcClient {
+            token: Some("s3cret".to_string()),
+            ..anonymous
+        };
+        assert!(matches!(
+            client
+                .call(RunnerRequest::Ping, Duration::from_secs(5))
+                .await
+                .unwrap(),
+            RunnerResponse::Pong
+        ));
+    }
+
+    #[test]
+    fn recognises_the_http2_preface() {
+        assert!(is_http2_preface(b"PRI * HTTP/2.0\r\n\r\nSM\r\n\r\n\0\0"));
+        assert!(is_http2_preface(b"PRI"));
+        assert!(!is_http2_preface(b"{\"type\":\"ping\"}\n"));
+        assert!(!is_http2_preface(b"AUTH token\n"));
+        assert!(!is_http2_preface(b""));
+    }
+}
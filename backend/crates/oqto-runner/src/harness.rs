@@ -0,0 +1,102 @@
+//! Loading agent harness manifests.
+//!
+//! Besides the built-in Pi harness, the runner spawns any harness declared by
+//! a `*.toml` manifest in its harness directory (`[runner] harness_dir`,
+//! default `~/.config/oqto/harnesses`). See [`oqto_protocol::harness`] for the
+//! manifest format. Invalid manifests are logged and skipped so one broken
+//! file does not take the runner down.
+
+use std::path::Path;
+
+use log::{info, warn};
+use oqto_protocol::harness::{HarnessManifest, HarnessRegistry};
+
+/// Registry with the built-in Pi harness plus the manifests in `dir`.
+pub fn load_registry(pi_binary: &str, dir: &Path) -> HarnessRegistry {
+    let mut registry = HarnessRegistry::with_pi(pi_binary);
+    let entries = match std::fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => return registry,
+        Err(err) => {
+            warn!(
+                "Failed to read harness directory {}: {}",
+                dir.display(),
+                err
+            );
+            return registry;
+        }
+    };
+
+    let mut paths: Vec<_> = entries
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "toml"))
+        .collect();
+    paths.sort();
+
+    for path in paths {
+        let manifest = match std::fs::read_to_string(&path)
+            .map_err(|err| err.to_string())
+            .and_then(|content| {
+                toml::from_str::<HarnessManifest>(&content).map_err(|err| err.to_string())
+            }) {
+            Ok(manifest) => manifest,
+            Err(err) => {
+                warn!("Skipping harness manifest {}: {}", path.display(), err);
+                continue;
+            }
+        };
+        let name = manifest.name.clone();
+        match registry.register(manifest) {
+            Ok(replaced) => info!(
+                "Registered harness '{}' from {}{}",
+                name,
+                path.display(),
+                if replaced.is_some() {
+                    " (overrides an earlier definition)"
+                } else {
+                    ""
+                }
+            ),
+            Err(err) => warn!("Skipping harness manifest {}: {}", path.display(), err),
+        }
+    }
+    registry
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_load_registry_reads_valid_manifests() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(
+            dir.path().join("wrapped.toml"),
+            r#"
+name = "wrapped"
+binary = "/opt/wrapped/bin/agent"
+args = ["--mode", "rpc", ["--model", "{model}"]]
+
+[env]
+WRAPPED_QUIET = "1"
+"#,
+        )
+        .unwrap();
+        std::fs::write(
+            dir.path().join("broken.toml"),
+            "name = \"broken\"\nbinary = \"x\"\nargs = [\"{bogus}\"]\n",
+        )
+        .unwrap();
+        std::fs::write(dir.path().join("notes.txt"), "ignored").unwrap();
+
+        let registry = load_registry("/usr/local/bin/pi", dir.path());
+        assert_eq!(registry.names(), vec!["pi", "wrapped"]);
+        let wrapped = registry.get(Some("wrapped")).unwrap();
+        assert_eq!(wrapped.binary, "/opt/wrapped/bin/agent");
+        assert_eq!(
+            wrapped.env.get("WRAPPED_QUIET").map(String::as_str),
+            Some("1")
+        );
+    }
+}
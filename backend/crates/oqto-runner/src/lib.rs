@@ -5,8 +5,28 @@
 //! modules that have not moved yet.
 
 pub mod agent_browser;
+pub mod artifacts;
+pub mod background;
+pub mod cgroup;
 pub mod client;
+pub mod crash_bundle;
 pub mod daemon;
+pub mod data_query;
+pub mod dependency_scan;
+pub mod file_history;
+pub mod git;
+pub mod grpc;
+pub mod harness;
+pub mod output_lint;
 pub mod pi_manager;
 pub mod pi_translator;
 pub mod protocol;
+pub mod remote;
+pub mod resource_usage;
+pub mod session_secrets;
+pub mod shell_env;
+pub mod smoke_check;
+pub mod tool_approval;
+pub mod tool_output;
+pub mod tool_rate_limit;
+pub mod workspace_archive;
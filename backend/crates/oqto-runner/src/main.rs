@@ -1,4 +1,4 @@
-use anyhow::Result;
+use anyhow::{Context, Result};
 use clap::Parser;
 use log::info;
 use std::path::PathBuf;
@@ -8,7 +8,8 @@ use oqto_runner::daemon::bootstrap::{
     get_default_socket_path, load_env_file, load_sandbox_config, log_sandbox_state,
 };
 use oqto_runner::daemon::config::RunnerUserConfig;
-use oqto_runner::daemon::server::{Runner, SessionBinaries};
+use oqto_runner::daemon::server::{GrpcListen, Runner, SessionBinaries, TcpListen};
+use oqto_runner::grpc::ServerTls;
 use oqto_runner::pi_manager::{PiManagerConfig, PiSessionManager};
 
 #[derive(Parser, Debug)]
@@ -31,6 +32,75 @@ struct Args {
     fileserver_binary: Option<String>,
     #[arg(long)]
     ttyd_binary: Option<String>,
+    /// Also accept backend connections over TCP on this address (remote
+    /// runner host), e.g. `0.0.0.0:7420`.
+    #[arg(long)]
+    listen: Option<std::net::SocketAddr>,
+    /// File holding the token TCP clients authenticate with (defaults to
+    /// `$OQTO_RUNNER_TOKEN`).
+    #[arg(long)]
+    token_file: Option<PathBuf>,
+    /// Also serve gRPC to remote backends on this address, e.g.
+    /// `0.0.0.0:7421`. Clients authenticate with the same token.
+    #[arg(long)]
+    grpc_listen: Option<std::net::SocketAddr>,
+    /// Certificate (PEM) for TLS on the gRPC port.
+    #[arg(long, requires = "tls_key")]
+    tls_cert: Option<PathBuf>,
+    /// Key (PEM) of `--tls-cert`.
+    #[arg(long, requires = "tls_cert")]
+    tls_key: Option<PathBuf>,
+    /// CA (PEM) gRPC clients' certificates must be signed by; clients
+    /// without one are refused.
+    #[arg(long, requires = "tls_cert")]
+    tls_client_ca: Option<PathBuf>,
+}
+
+/// The TCP listener configuration, when `--listen` is given.
+fn tcp_listen(args: &Args) -> Result<Option<TcpListen>> {
+    let Some(address) = args.listen else {
+        return Ok(None);
+    };
+    let token = token(args, "--listen")?;
+    Ok(Some(TcpListen { address, token }))
+}
+
+/// The gRPC listener configuration, when `--grpc-listen` is given.
+fn grpc_listen(args: &Args) -> Result<Option<GrpcListen>> {
+    let Some(address) = args.grpc_listen else {
+        return Ok(None);
+    };
+    let token = token(args, "--grpc-listen")?;
+    let tls = match (&args.tls_cert, &args.tls_key) {
+        (Some(cert), Some(key)) => Some(ServerTls {
+            cert: cert.clone(),
+            key: key.clone(),
+            client_ca: args.tls_client_ca.clone(),
+        }),
+        _ => None,
+    };
+    Ok(Some(GrpcListen {
+        address,
+        token,
+        tls,
+    }))
+}
+
+/// The token remote clients authenticate with, required by `flag`.
+fn token(args: &Args, flag: &str) -> Result<String> {
+    let token = match &args.token_file {
+        Some(path) => std::fs::read_to_string(path)
+            .with_context(|| format!("reading token file {}", path.display()))?,
+        None => std::env::var(oqto_runner::remote::TOKEN_ENV).unwrap_or_default(),
+    };
+    let token = token.trim().to_string();
+    if token.is_empty() {
+        anyhow::bail!(
+            "{flag} requires a token (--token-file or ${})",
+            oqto_runner::remote::TOKEN_ENV
+        );
+    }
+    Ok(token)
 }
 
 #[tokio::main]
@@ -40,7 +110,7 @@ async fn main() -> Result<()> {
     let log_level = if args.verbose { "debug" } else { "info" };
     env_logger::Builder::from_env(env_logger::Env::default().default_filter_or(log_level)).init();
 
-    let socket_path = args.socket.unwrap_or_else(get_default_socket_path);
+    let socket_path = args.socket.clone().unwrap_or_else(get_default_socket_path);
 
     info!(
         "Starting oqto-runner (user={}, socket={:?})",
@@ -49,6 +119,8 @@ async fn main() -> Result<()> {
     );
 
     load_env_file();
+    let tcp_listen = tcp_listen(&args)?;
+    let grpc_listen = grpc_listen(&args)?;
 
     let user_config = args
         .config
@@ -110,6 +182,22 @@ async fn main() -> Result<()> {
         sandbox_config: sandbox_config.clone(),
         runner_id: user_config.runner_id.clone(),
         model_cache_dir: Some(state_dir.join("oqto").join("model-cache")),
+        crash_bundle_dir: Some(state_dir.join("oqto").join("crash-bundles")),
+        artifact_dir: Some(state_dir.join("oqto").join("artifacts")),
+        file_history_dir: user_config
+            .file_history
+            .enabled
+            .then(|| state_dir.join("oqto").join("file-history")),
+        file_history: user_config.file_history.clone(),
+        tool_rate_limits: user_config.tool_rate_limits.clone(),
+        tool_approvals: user_config.tool_approvals.clone(),
+        tool_output: user_config.tool_output.clone(),
+        output_lint: user_config.output_lint.clone(),
+        artifacts: user_config.artifacts.clone(),
+        harnesses: oqto_runner::harness::load_registry(
+            &user_config.pi_binary,
+            &user_config.harness_dir,
+        ),
     };
     let pi_manager = PiSessionManager::new(pi_config);
 
@@ -128,7 +216,20 @@ async fn main() -> Result<()> {
         memories_dir: user_config.memories_dir.clone(),
         single_user: user_config.single_user,
         linux_users_enabled: user_config.linux_users_enabled,
+        tool_rate_limits: user_config.tool_rate_limits.clone(),
+        tool_approvals: user_config.tool_approvals.clone(),
+        tool_output: user_config.tool_output.clone(),
+        output_lint: user_config.output_lint.clone(),
+        artifacts: user_config.artifacts.clone(),
+        file_history: user_config.file_history.clone(),
+        harness_dir: user_config.harness_dir.clone(),
     };
-    let runner = Runner::new(sandbox_config, binaries, legacy_user_config, pi_manager);
+    let mut runner = Runner::new(sandbox_config, binaries, legacy_user_config, pi_manager);
+    if let Some(listen) = tcp_listen {
+        runner = runner.with_tcp_listen(listen);
+    }
+    if let Some(listen) = grpc_listen {
+        runner = runner.with_grpc_listen(listen);
+    }
     runner.run(&socket_path).await
 }
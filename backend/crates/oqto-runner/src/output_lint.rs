@@ -0,0 +1,566 @@
+//! Lint hooks for assistant output.
+//!
+//! When an assistant message ends, its text is passed through a chain of
+//! hooks before the runner stores or forwards the finished message. A hook
+//! can let it pass, annotate it, or block it, in which case the text is
+//! replaced by `placeholder` both in the `stream.message_end` event and in
+//! the turn's persisted history. Findings are reported as a
+//! `stream.message_lint` event. The text deltas streamed while the message
+//! was generated are not held back; clients replace them on a block.
+//!
+//! Hooks are external commands (a WASM filter runs the same way through a
+//! WASI runtime, e.g. `command = "wasmtime"`, `args = ["run", "filter.wasm"]`)
+//! or built-in checks. A command gets a JSON object on stdin:
+//!
+//! ```json
+//! {"session_id": "...", "text": "...", "prompt": "...", "provider": "...", "model": "..."}
+//! ```
+//!
+//! and answers on stdout with `{"verdict": "pass" | "annotate" | "block",
+//! "message": "..."}` (no output passes). Each hook has its own timeout and
+//! the whole chain a latency budget; a hook that times out, fails or cannot
+//! be run is bypassed, so a broken hook never holds a message back. The
+//! built-in `json` check blocks malformed JSON when the prompt asked for JSON.
+//!
+//! ```toml
+//! [runner.output_lint]
+//! enabled = true
+//! budget_ms = 2000
+//!
+//! [[runner.output_lint.hooks]]
+//! name = "company-names"
+//! command = "/usr/local/bin/name-policy"
+//! timeout_ms = 500
+//!
+//! [[runner.output_lint.hooks]]
+//! name = "json"
+//! builtin = "json"
+//! annotate_only = true
+//! ```
+
+use std::process::Stdio;
+use std::time::{Duration, Instant};
+
+use log::warn;
+use oqto_pi::AgentMessage;
+use oqto_protocol::events::{LintFinding, LintVerdict};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tokio::io::AsyncWriteExt;
+use tokio::process::Command;
+
+/// Bytes of a failing hook's stderr included in its finding.
+const STDERR_TAIL_BYTES: usize = 200;
+
+/// Output lint configuration (`[runner.output_lint]`).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct OutputLintConfig {
+    pub enabled: bool,
+    /// Time the whole chain may add to a message. Hooks that would run past
+    /// it are bypassed.
+    pub budget_ms: u64,
+    /// Text that replaces a blocked message. `{hook}` and `{reason}` are
+    /// filled in.
+    pub placeholder: String,
+    /// Run in order; the first block ends the chain.
+    pub hooks: Vec<LintHook>,
+}
+
+impl Default for OutputLintConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            budget_ms: 2000,
+            placeholder: "[This response was withheld by the output policy ({hook}): {reason}]"
+                .to_string(),
+            hooks: Vec::new(),
+        }
+    }
+}
+
+/// One hook of the chain: an external `command` or a `builtin` check.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LintHook {
+    /// Reported in `stream.message_lint` findings.
+    pub name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub command: Option<String>,
+    #[serde(default)]
+    pub args: Vec<String>,
+    /// Built-in check: `json`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub builtin: Option<String>,
+    #[serde(default = "default_hook_timeout_ms")]
+    pub timeout_ms: u64,
+    /// Report blocks as annotations instead of withholding the message.
+    #[serde(default)]
+    pub annotate_only: bool,
+}
+
+fn default_hook_timeout_ms() -> u64 {
+    1000
+}
+
+#[derive(Debug, Clone)]
+enum HookKind {
+    Command { command: String, args: Vec<String> },
+    Json,
+}
+
+#[derive(Debug, Clone)]
+struct Hook {
+    name: String,
+    kind: HookKind,
+    timeout: Duration,
+    annotate_only: bool,
+}
+
+/// Outcome of the chain for one message.
+#[derive(Debug, Clone)]
+pub struct LintReport {
+    pub blocked: bool,
+    pub findings: Vec<LintFinding>,
+}
+
+#[derive(Serialize)]
+struct HookInput<'a> {
+    session_id: &'a str,
+    text: &'a str,
+    prompt: &'a str,
+    provider: Option<&'a str>,
+    model: Option<&'a str>,
+}
+
+#[derive(Deserialize)]
+struct HookOutput {
+    verdict: HookVerdict,
+    #[serde(default)]
+    message: Option<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum HookVerdict {
+    Pass,
+    Annotate,
+    Block,
+}
+
+/// Output lint state of one session.
+#[derive(Debug)]
+pub struct OutputLinter {
+    hooks: Vec<Hook>,
+    budget: Duration,
+    placeholder: String,
+    /// Text of the latest user prompt.
+    prompt: String,
+    /// (original, replacement) text of messages blocked this turn, applied
+    /// to the `agent_end` snapshot before it is persisted.
+    withheld: Vec<(String, String)>,
+}
+
+impl OutputLinter {
+    /// None when linting is disabled or no hook is usable.
+    pub fn new(config: &OutputLintConfig) -> Option<Self> {
+        if !config.enabled {
+            return None;
+        }
+        let hooks: Vec<Hook> = config
+            .hooks
+            .iter()
+            .filter_map(|hook| {
+                let kind = match (&hook.command, hook.builtin.as_deref()) {
+                    (Some(command), None) => HookKind::Command {
+                        command: command.clone(),
+                        args: hook.args.clone(),
+                    },
+                    (None, Some("json")) => HookKind::Json,
+                    _ => {
+                        warn!(
+                            "Output lint hook '{}' needs either a command or a known builtin; skipping it",
+                            hook.name
+                        );
+                        return None;
+                    }
+                };
+                Some(Hook {
+                    name: hook.name.clone(),
+                    kind,
+                    timeout: Duration::from_millis(hook.timeout_ms),
+                    annotate_only: hook.annotate_only,
+                })
+            })
+            .collect();
+        if hooks.is_empty() {
+            return None;
+        }
+        Some(Self {
+            hooks,
+            budget: Duration::from_millis(config.budget_ms),
+            placeholder: config.placeholder.clone(),
+            prompt: String::new(),
+            withheld: Vec::new(),
+        })
+    }
+
+    /// Remember the prompt the next assistant messages answer.
+    pub fn observe_prompt(&mut self, message: &AgentMessage) {
+        if message.role.eq_ignore_ascii_case("user") {
+            self.prompt = message_text(message);
+        }
+    }
+
+    /// Run the chain on a finished assistant message, replacing its text
+    /// when a hook blocks it. None when there was nothing to report.
+    pub async fn check(
+        &mut self,
+        session_id: &str,
+        message: &mut AgentMessage,
+    ) -> Option<LintReport> {
+        if !message.role.eq_ignore_ascii_case("assistant") {
+            return None;
+        }
+        let text = message_text(message);
+        if text.trim().is_empty() {
+            return None;
+        }
+        let input = HookInput {
+            session_id,
+            text: &text,
+            prompt: &self.prompt,
+            provider: message.provider.as_deref(),
+            model: message.model.as_deref(),
+        };
+        let report = self.run_chain(&input).await;
+        if report.findings.is_empty() {
+            return None;
+        }
+        if report.blocked
+            && let Some(block) = report
+                .findings
+                .iter()
+                .find(|f| f.verdict == LintVerdict::Block)
+        {
+            let replacement = self
+                .placeholder
+                .replace("{hook}", &block.hook)
+                .replace("{reason}", block.message.as_deref().unwrap_or("blocked"));
+            replace_text(message, &replacement);
+            self.withheld.push((text, replacement));
+        }
+        Some(report)
+    }
+
+    /// Apply this turn's blocks to the messages of its `agent_end`.
+    pub fn apply_withheld(&mut self, messages: &mut [AgentMessage]) {
+        for (original, replacement) in self.withheld.drain(..) {
+            if let Some(message) = messages
+                .iter_mut()
+                .find(|m| m.role.eq_ignore_ascii_case("assistant") && message_text(m) == original)
+            {
+                replace_text(message, &replacement);
+            }
+        }
+    }
+
+    async fn run_chain(&self, input: &HookInput<'_>) -> LintReport {
+        let deadline = Instant::now() + self.budget;
+        let mut findings = Vec::new();
+        let mut blocked = false;
+        for hook in &self.hooks {
+            let started = Instant::now();
+            let remaining = deadline.saturating_duration_since(started);
+            let result = if remaining.is_zero() {
+                Some((
+                    LintVerdict::Bypassed,
+                    Some("latency budget exhausted".to_string()),
+                ))
+            } else {
+                self.run_hook(hook, input, hook.timeout.min(remaining))
+                    .await
+            };
+            let Some((mut verdict, message)) = result else {
+                continue;
+            };
+            if verdict == LintVerdict::Block && hook.annotate_only {
+                verdict = LintVerdict::Annotate;
+            }
+            if verdict == LintVerdict::Bypassed {
+                warn!(
+                    "Output lint hook '{}' bypassed: {}",
+                    hook.name,
+                    message.as_deref().unwrap_or("")
+                );
+            }
+            findings.push(LintFinding {
+                hook: hook.name.clone(),
+                verdict,
+                message,
+                duration_ms: started.elapsed().as_millis() as u64,
+            });
+            if verdict == LintVerdict::Block {
+                blocked = true;
+                break;
+            }
+        }
+        LintReport { blocked, findings }
+    }
+
+    /// Verdict of one hook; None when it passed.
+    async fn run_hook(
+        &self,
+        hook: &Hook,
+        input: &HookInput<'_>,
+        timeout: Duration,
+    ) -> Option<(LintVerdict, Option<String>)> {
+        match &hook.kind {
+            HookKind::Json => {
+                check_json(input.prompt, input.text).map(|error| (LintVerdict::Block, Some(error)))
+            }
+            HookKind::Command { command, args } => {
+                match tokio::time::timeout(timeout, run_command(command, args, input)).await {
+                    Ok(Ok(output)) => match output.verdict {
+                        HookVerdict::Pass => None,
+                        HookVerdict::Annotate => Some((LintVerdict::Annotate, output.message)),
+                        HookVerdict::Block => Some((LintVerdict::Block, output.message)),
+                    },
+                    Ok(Err(error)) => Some((LintVerdict::Bypassed, Some(error))),
+                    Err(_) => Some((
+                        LintVerdict::Bypassed,
+                        Some(format!("timed out after {}ms", timeout.as_millis())),
+                    )),
+                }
+            }
+        }
+    }
+}
+
+/// Run a command hook. The child is killed if the future is dropped on a
+/// timeout.
+async fn run_command(
+    command: &str,
+    args: &[String],
+    input: &HookInput<'_>,
+) -> Result<HookOutput, String> {
+    let mut child = Command::new(command)
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true)
+        .spawn()
+        .map_err(|e| format!("failed to start {command}: {e}"))?;
+    if let Some(mut stdin) = child.stdin.take() {
+        let body = serde_json::to_vec(input).unwrap_or_default();
+        // A hook that ignores its input may exit before reading it.
+        let _ = stdin.write_all(&body).await;
+    }
+    let output = child
+        .wait_with_output()
+        .await
+        .map_err(|e| format!("failed to run {command}: {e}"))?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        let stderr = stderr.trim();
+        let start = stderr
+            .char_indices()
+            .map(|(i, _)| i)
+            .find(|&i| stderr.len() - i <= STDERR_TAIL_BYTES)
+            .unwrap_or(stderr.len());
+        return Err(format!(
+            "exited with {}: {}",
+            output.status,
+            &stderr[start..]
+        ));
+    }
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    if stdout.trim().is_empty() {
+        return Ok(HookOutput {
+            verdict: HookVerdict::Pass,
+            message: None,
+        });
+    }
+    serde_json::from_str(stdout.trim()).map_err(|e| format!("invalid hook output: {e}"))
+}
+
+/// Error when the prompt asked for JSON and the answer is not valid JSON.
+/// Fenced ```json blocks are checked when there are any, otherwise the
+/// whole answer.
+fn check_json(prompt: &str, text: &str) -> Option<String> {
+    if !prompt.to_lowercase().contains("json") {
+        return None;
+    }
+    let mut blocks = Vec::new();
+    let mut rest = text;
+    while let Some(start) = rest.find("```json") {
+        let body = &rest[start + "```json".len()..];
+        let Some(end) = body.find("```") else {
+            return Some("unterminated ```json block".to_string());
+        };
+        blocks.push(&body[..end]);
+        rest = &body[end + 3..];
+    }
+    if blocks.is_empty() {
+        blocks.push(text);
+    }
+    blocks.iter().find_map(|block| {
+        serde_json::from_str::<Value>(block.trim())
+            .err()
+            .map(|e| format!("malformed JSON: {e}"))
+    })
+}
+
+/// Text parts of a message, joined by newlines.
+fn message_text(message: &AgentMessage) -> String {
+    match &message.content {
+        Value::String(text) => text.clone(),
+        Value::Array(parts) => parts
+            .iter()
+            .filter(|part| part.get("type").and_then(Value::as_str) == Some("text"))
+            .filter_map(|part| part.get("text").and_then(Value::as_str))
+            .collect::<Vec<_>>()
+            .join("\n"),
+        _ => String::new(),
+    }
+}
+
+/// Replace the text parts of a message with a single one, keeping its other
+/// parts (thinking, tool calls) in place.
+fn replace_text(message: &mut AgentMessage, replacement: &str) {
+    match &mut message.content {
+        Value::Array(parts) => {
+            let is_text = |part: &Value| part.get("type").and_then(Value::as_str) == Some("text");
+            let at = parts.iter().position(is_text).unwrap_or(parts.len());
+            parts.retain(|part| !is_text(part));
+            parts.insert(
+                at.min(parts.len()),
+                serde_json::json!({ "type": "text", "text": replacement }),
+            );
+        }
+        content => *content = Value::String(replacement.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn message(role: &str, content: Value) -> AgentMessage {
+        serde_json::from_value(serde_json::json!({ "role": role, "content": content })).unwrap()
+    }
+
+    fn linter(hooks: &str) -> OutputLinter {
+        let config: OutputLintConfig =
+            toml::from_str(&format!("enabled = true\nbudget_ms = 2000\n{hooks}")).unwrap();
+        OutputLinter::new(&config).unwrap()
+    }
+
+    #[test]
+    fn disabled_or_empty_config_has_no_linter() {
+        assert!(OutputLinter::new(&OutputLintConfig::default()).is_none());
+        let config: OutputLintConfig =
+            toml::from_str("enabled = true\n[[hooks]]\nname = \"broken\"\nbuiltin = \"nope\"\n")
+                .unwrap();
+        assert!(OutputLinter::new(&config).is_none());
+    }
+
+    #[test]
+    fn json_check_only_applies_when_requested() {
+        assert_eq!(check_json("say hi", "{oops"), None);
+        assert_eq!(check_json("Answer in JSON", "{\"a\": 1}"), None);
+        assert!(check_json("Answer in JSON", "{\"a\": 1").is_some());
+        assert_eq!(
+            check_json("json please", "Here:\n```json\n[1, 2]\n```\nDone."),
+            None
+        );
+        assert!(check_json("json please", "```json\n[1, 2\n```").is_some());
+    }
+
+    #[test]
+    fn replacing_text_keeps_other_parts() {
+        let mut msg = message(
+            "assistant",
+            serde_json::json!([
+                { "type": "thinking", "thinking": "hmm" },
+                { "type": "text", "text": "secret" },
+                { "type": "toolCall", "id": "t1" },
+                { "type": "text", "text": "more" },
+            ]),
+        );
+        assert_eq!(message_text(&msg), "secret\nmore");
+        replace_text(&mut msg, "withheld");
+        let parts = msg.content.as_array().unwrap();
+        assert_eq!(parts.len(), 3);
+        assert_eq!(parts[1]["text"], "withheld");
+        assert_eq!(parts[2]["type"], "toolCall");
+    }
+
+    #[tokio::test]
+    async fn blocking_hook_withholds_the_message() {
+        let mut linter = linter(
+            r#"
+[[hooks]]
+name = "names"
+command = "sh"
+args = ["-c", "grep -q Acme && echo '{\"verdict\":\"block\",\"message\":\"mentions Acme\"}' || true"]
+"#,
+        );
+        let mut clean = message(
+            "assistant",
+            serde_json::json!([{ "type": "text", "text": "fine" }]),
+        );
+        assert!(linter.check("s1", &mut clean).await.is_none());
+
+        let original = serde_json::json!([{ "type": "text", "text": "Acme rocks" }]);
+        let mut msg = message("assistant", original.clone());
+        let report = linter.check("s1", &mut msg).await.unwrap();
+        assert!(report.blocked);
+        assert_eq!(report.findings[0].message.as_deref(), Some("mentions Acme"));
+        assert!(message_text(&msg).contains("mentions Acme"));
+
+        let mut turn = vec![
+            message("user", serde_json::json!("hi")),
+            message("assistant", original),
+        ];
+        linter.apply_withheld(&mut turn);
+        assert_eq!(message_text(&turn[1]), message_text(&msg));
+    }
+
+    #[tokio::test]
+    async fn slow_or_failing_hooks_are_bypassed() {
+        let mut linter = linter(
+            r#"
+[[hooks]]
+name = "slow"
+command = "sleep"
+args = ["5"]
+timeout_ms = 100
+
+[[hooks]]
+name = "broken"
+command = "sh"
+args = ["-c", "exit 3"]
+
+[[hooks]]
+name = "strict"
+command = "sh"
+args = ["-c", "echo '{\"verdict\":\"block\"}'"]
+annotate_only = true
+"#,
+        );
+        let mut msg = message("assistant", serde_json::json!("hello"));
+        let report = linter.check("s1", &mut msg).await.unwrap();
+        assert!(!report.blocked);
+        let verdicts: Vec<_> = report.findings.iter().map(|f| f.verdict).collect();
+        assert_eq!(
+            verdicts,
+            [
+                LintVerdict::Bypassed,
+                LintVerdict::Bypassed,
+                LintVerdict::Annotate
+            ]
+        );
+        assert_eq!(msg.content, "hello");
+    }
+}
@@ -11,6 +11,7 @@
 use std::collections::{HashMap, VecDeque};
 use std::path::{Path, PathBuf};
 use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::time::{Duration, Instant};
 
 #[cfg(unix)]
@@ -25,10 +26,28 @@ use tokio::process::{Child, ChildStdin, Command};
 use tokio::sync::{Mutex, OwnedSemaphorePermit, RwLock, Semaphore, broadcast, mpsc, oneshot};
 
 use crate::agent_browser::{agent_browser_session_dir, browser_session_name};
+use crate::artifacts::{
+    ARTIFACTS_DIR_ENV, ArtifactConfig, ArtifactStore, ArtifactWatch, session_artifacts_dir,
+};
+use crate::cgroup::{AgentCgroups, SessionCgroup};
+use crate::crash_bundle::{CrashBundleStore, CrashContext, DEFAULT_KEEP_BUNDLES, is_abnormal_exit};
+use crate::file_history::{FileHistoryConfig, FileHistoryStore};
+use crate::output_lint::{OutputLintConfig, OutputLinter};
 use crate::pi_translator::PiTranslator;
-use crate::protocol::{ChatMessageProto, PiSessionInfo, PiSessionState, agent_msg_to_chat_proto};
+use crate::protocol::{
+    ChatMessageProto, PiSessionInfo, PiSessionState, ToolApproval, agent_msg_to_chat_proto,
+};
+use crate::tool_approval::{
+    ApprovalCall, ApprovalGate, ToolApprovalConfig, ToolApprovalPolicy, decision_value,
+    install_bridge_extension,
+};
+use crate::tool_output::ToolOutputConfig;
+use crate::tool_rate_limit::{ToolRateLimitConfig, ToolRateLimiter};
 use oqto_pi::{AgentMessage, PiCommand, PiEvent, PiMessage, PiResponse, PiState, SessionStats};
-use oqto_protocol::events::{AgentPhase, Event as CanonicalEvent, EventPayload};
+use oqto_protocol::events::{
+    AgentPhase, Event as CanonicalEvent, EventPayload, InterjectionDelivery,
+};
+use oqto_protocol::harness::{DEFAULT_HARNESS, HarnessLaunch, HarnessManifest, HarnessRegistry};
 use oqto_sandbox::{EgressGuard, SandboxConfig, configure_bwrap_pre_exec};
 
 // ============================================================================
@@ -53,6 +72,30 @@ pub struct PiManagerConfig {
     /// Directory for persisting the model cache across restarts.
     /// Each workdir gets its own JSON file: `<cache_dir>/models/<hash>.json`
     pub model_cache_dir: Option<PathBuf>,
+    /// Directory for forensic bundles of crashed Pi processes (None disables
+    /// collection).
+    pub crash_bundle_dir: Option<PathBuf>,
+    /// Store for files agents write to their artifacts directory (None
+    /// disables capture).
+    pub artifact_dir: Option<PathBuf>,
+    /// Workspace globs and retention of captured artifacts.
+    pub artifacts: ArtifactConfig,
+    /// Shadow repositories for per-turn workspace snapshots (None disables
+    /// file history).
+    pub file_history_dir: Option<PathBuf>,
+    /// File size limit and retention of workspace snapshots.
+    pub file_history: FileHistoryConfig,
+    /// Per-session tool call limits.
+    pub tool_rate_limits: ToolRateLimitConfig,
+    /// Tool calls that wait for a user's approval.
+    pub tool_approvals: ToolApprovalConfig,
+    /// Streaming of running tools' output.
+    pub tool_output: ToolOutputConfig,
+    /// Hooks that check assistant output before it is delivered.
+    pub output_lint: OutputLintConfig,
+    /// Harnesses sessions can select. Sessions without a harness (or with
+    /// `pi` when the registry has none) spawn `pi_binary`.
+    pub harnesses: HarnessRegistry,
 }
 
 impl Default for PiManagerConfig {
@@ -75,6 +118,7 @@ impl Default for PiManagerConfig {
         };
 
         Self {
+            harnesses: HarnessRegistry::with_pi(pi_binary.to_string_lossy()),
             pi_binary,
             default_cwd: PathBuf::from(&home).join("projects"),
             idle_timeout_secs: 300, // 5 minutes
@@ -82,6 +126,15 @@ impl Default for PiManagerConfig {
             sandbox_config: None,
             runner_id: "local".to_string(),
             model_cache_dir: Some(state_dir.join("oqto").join("model-cache")),
+            crash_bundle_dir: Some(state_dir.join("oqto").join("crash-bundles")),
+            artifact_dir: Some(state_dir.join("oqto").join("artifacts")),
+            artifacts: ArtifactConfig::default(),
+            file_history_dir: Some(state_dir.join("oqto").join("file-history")),
+            file_history: FileHistoryConfig::default(),
+            tool_rate_limits: ToolRateLimitConfig::default(),
+            tool_approvals: ToolApprovalConfig::default(),
+            tool_output: ToolOutputConfig::default(),
+            output_lint: OutputLintConfig::default(),
         }
     }
 }
@@ -106,6 +159,9 @@ struct ModelCacheEntry {
 /// models.json + ephemeral Pi so new provider models are picked up.
 const MODEL_CACHE_TTL: std::time::Duration = std::time::Duration::from_secs(3600);
 
+/// How often a capped session's cgroup is checked for OOM kills.
+const OOM_WATCH_INTERVAL: Duration = Duration::from_secs(2);
+
 // ============================================================================
 // Session Configuration (per-session)
 // ============================================================================
@@ -130,6 +186,13 @@ pub struct PiSessionConfig {
     /// Environment variables.
     #[serde(default)]
     pub env: HashMap<String, String>,
+    /// Harness to spawn (None = Pi).
+    #[serde(default)]
+    pub harness: Option<String>,
+    /// Sandbox profile nested inside the runner's sandbox config (None =
+    /// the config as it is).
+    #[serde(default)]
+    pub sandbox_profile: Option<String>,
 }
 
 impl Default for PiSessionConfig {
@@ -142,6 +205,8 @@ impl Default for PiSessionConfig {
             session_file: None,
             continue_session: None,
             env: HashMap::new(),
+            harness: None,
+            sandbox_profile: None,
         }
     }
 }
@@ -389,6 +454,17 @@ type PendingResponses = Arc<RwLock<HashMap<String, oneshot::Sender<PiResponse>>>
 /// events are unavailable.
 type PendingClientId = Arc<Mutex<VecDeque<String>>>;
 
+/// Interjections held until the running turn ends, for harnesses that take
+/// no steering messages (shared between the manager and the reader task).
+type InterjectionQueue = Arc<Mutex<Vec<QueuedInterjection>>>;
+
+#[derive(Debug, Clone)]
+struct QueuedInterjection {
+    id: String,
+    message: String,
+    client_id: Option<String>,
+}
+
 /// The Pi external_id for a session.
 ///
 /// Empty until Pi reports its native `sessionId` via `get_state`, then fixed
@@ -431,12 +507,22 @@ struct PiSession {
     active_provider: Arc<RwLock<Option<String>>>,
     /// Last known active model from Pi `get_state`.
     active_model: Arc<RwLock<Option<String>>>,
-    /// Child process.
-    process: Child,
+    /// Child process (shared with the reader task, which reaps it on exit).
+    process: Arc<Mutex<Child>>,
+    /// OS PID captured at spawn.
+    pid: Option<u32>,
+    /// Set when the runner stops the process on purpose, so the exit is not
+    /// reported as a crash.
+    closing: Arc<AtomicBool>,
+    /// Sandbox profile in effect (None = unsandboxed).
+    sandbox_profile: Option<String>,
     /// Egress namespace guard (proxy mode). Held for the session lifetime so
     /// its `Drop` tears the namespace down when the session ends; inert for
     /// open/isolated modes. The runner never touches the namespace directly.
     _egress_guard: EgressGuard,
+    /// cgroup leaf with the session's resource caps. Removed when the
+    /// session ends; its OOM watcher stops with it.
+    _cgroup: Option<Arc<SessionCgroup>>,
     /// Current state.
     state: Arc<RwLock<PiSessionState>>,
     /// The Pi external_id for this session.
@@ -459,6 +545,12 @@ struct PiSession {
     /// Populated on AgentEnd and seeded from oqto-log on resume.
     /// `get_message_buffer()` returns this directly -- no Pi RPC needed.
     message_buffer: MessageBuffer,
+    /// Tool calls waiting for a user's approval (shared with reader task).
+    approvals: Arc<ApprovalGate>,
+    /// Whether the harness takes steering messages mid-turn.
+    steering: bool,
+    /// Interjections waiting for the turn to end (shared with reader task).
+    interjections: InterjectionQueue,
     /// Handle to the background reader task.
     _reader_handle: tokio::task::JoinHandle<()>,
     /// Handle to the command processor task.
@@ -472,7 +564,30 @@ impl PiSession {
 
     /// Return the OS PID of the child process, if available.
     fn child_pid(&self) -> Option<u32> {
-        self.process.id()
+        self.pid
+    }
+}
+
+/// Reader-side handle for detecting crashes once stdout closes.
+struct CrashWatch {
+    process: Arc<Mutex<Child>>,
+    closing: Arc<AtomicBool>,
+    store: Arc<CrashBundleStore>,
+    context: CrashContext,
+}
+
+impl CrashWatch {
+    /// Poll for the exit status for a short while after stdout closed. The
+    /// child lock is never held across a sleep so `close_session` can still
+    /// kill the process.
+    async fn exit_status(&self) -> Option<std::process::ExitStatus> {
+        for _ in 0..40 {
+            if let Ok(Some(status)) = self.process.lock().await.try_wait() {
+                return Some(status);
+            }
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        }
+        None
     }
 }
 
@@ -510,6 +625,14 @@ pub struct PiSessionManager {
     models_json_mtime: RwLock<Option<std::time::SystemTime>>,
     /// Map Pi native session IDs back to the runner session key.
     session_aliases: Arc<RwLock<HashMap<String, String>>>,
+    /// Forensic bundle store for crashed Pi processes.
+    crash_bundles: Option<Arc<CrashBundleStore>>,
+    /// Store for captured agent artifacts.
+    artifacts: Option<Arc<ArtifactStore>>,
+    /// Per-turn workspace snapshots.
+    file_history: Option<Arc<FileHistoryStore>>,
+    /// cgroup v2 caps for agents (local mode, `[resources]` in sandbox.toml).
+    cgroups: Option<AgentCgroups>,
 }
 
 impl PiSessionManager {
@@ -519,6 +642,33 @@ impl PiSessionManager {
 
         // Load persisted model cache from disk
         let model_cache = Self::load_model_cache_from_disk(config.model_cache_dir.as_deref());
+        let crash_bundles = config
+            .crash_bundle_dir
+            .clone()
+            .map(|dir| Arc::new(CrashBundleStore::new(dir, DEFAULT_KEEP_BUNDLES)));
+        let artifacts = config
+            .artifact_dir
+            .clone()
+            .map(|dir| Arc::new(ArtifactStore::new(dir, &config.artifacts)));
+        let file_history = config
+            .file_history_dir
+            .clone()
+            .map(|dir| Arc::new(FileHistoryStore::new(dir, &config.file_history)));
+        let cgroups = config
+            .sandbox_config
+            .as_ref()
+            .map(|sandbox| &sandbox.resources)
+            .filter(|resources| !resources.is_empty())
+            .and_then(|resources| match AgentCgroups::setup(resources) {
+                Ok(cgroups) => {
+                    info!("Capping agent processes with cgroup v2: {:?}", resources);
+                    Some(cgroups)
+                }
+                Err(e) => {
+                    warn!("Resource caps from sandbox.toml are NOT enforced: {:#}", e);
+                    None
+                }
+            });
 
         Arc::new(Self {
             sessions: RwLock::new(HashMap::new()),
@@ -528,9 +678,50 @@ impl PiSessionManager {
             model_cache: RwLock::new(model_cache),
             models_json_mtime: RwLock::new(None),
             session_aliases: Arc::new(RwLock::new(HashMap::new())),
+            crash_bundles,
+            artifacts,
+            file_history,
+            cgroups,
         })
     }
 
+    /// Crash bundle store, if collection is enabled.
+    pub fn crash_bundles(&self) -> Option<&CrashBundleStore> {
+        self.crash_bundles.as_deref()
+    }
+
+    /// Artifact store, if capture is enabled.
+    pub fn artifacts(&self) -> Option<&ArtifactStore> {
+        self.artifacts.as_deref()
+    }
+
+    /// File history store, if per-turn snapshots are enabled.
+    pub fn file_history(&self) -> Option<&FileHistoryStore> {
+        self.file_history.as_deref()
+    }
+
+    /// Manifest of the harness a session selected (None = Pi).
+    fn harness_manifest(&self, name: Option<&str>) -> Result<HarnessManifest> {
+        if let Some(manifest) = self.config.harnesses.get(name) {
+            return Ok(manifest.clone());
+        }
+        match name.filter(|n| !n.is_empty()) {
+            None | Some(DEFAULT_HARNESS) => {
+                Ok(HarnessManifest::pi(self.config.pi_binary.to_string_lossy()))
+            }
+            Some(other) => anyhow::bail!("Unknown agent harness '{other}'"),
+        }
+    }
+
+    /// Names of the harnesses sessions can select.
+    pub fn harness_names(&self) -> Vec<String> {
+        let mut names = self.config.harnesses.names();
+        if !names.iter().any(|name| name == DEFAULT_HARNESS) {
+            names.insert(0, DEFAULT_HARNESS.to_string());
+        }
+        names
+    }
+
     /// Create a new session.
     ///
     /// Returns the **real** session ID assigned by Pi (which may differ from
@@ -626,34 +817,65 @@ impl PiSessionManager {
 
         let session_socket_dir_str = session_socket_dir.to_string_lossy().to_string();
 
-        // Build Pi arguments
-        let mut pi_args: Vec<String> = vec![
-            "--mode".to_string(),
-            "rpc".to_string(),
-            "--approve".to_string(),
-        ];
-
-        if let Some(ref provider) = config.provider {
-            pi_args.push("--provider".to_string());
-            pi_args.push(provider.clone());
-        }
-        if let Some(ref model) = config.model {
-            pi_args.push("--model".to_string());
-            pi_args.push(model.clone());
+        // Build harness arguments. Every harness speaks the Pi RPC protocol
+        // (the only adapter so far), so the rest of the session is the same.
+        let harness = self.harness_manifest(config.harness.as_deref())?;
+        let harness_binary = PathBuf::from(&harness.binary);
+        let mut pi_args = harness.render_args(&HarnessLaunch {
+            session_id: session_id.clone(),
+            cwd: config.cwd.to_string_lossy().to_string(),
+            provider: config.provider.clone(),
+            model: config.model.clone(),
+            session_file: session_file
+                .as_ref()
+                .map(|path| path.to_string_lossy().to_string()),
+        });
+        // Approval rules need the bridge extension that asks the runner
+        // before each tool call. Without it calls would run ungated, so the
+        // session does not start.
+        let approval_policy = ToolApprovalPolicy::new(&self.config.tool_approvals);
+        if approval_policy.is_some() {
+            let extension = install_bridge_extension(&config.cwd)
+                .context("Failed to install the tool approval extension")?;
+            pi_args.push("--extension".to_string());
+            pi_args.push(extension.to_string_lossy().to_string());
         }
-        if let Some(ref session_file) = session_file {
-            pi_args.push("--session".to_string());
-            pi_args.push(session_file.to_string_lossy().to_string());
+        if harness.name != DEFAULT_HARNESS {
+            info!(
+                "Session '{}' uses harness '{}' ({})",
+                session_id,
+                harness.name,
+                harness_binary.display()
+            );
         }
         // Build command - either direct or via bwrap sandbox
         let mut bwrap_pre_exec_config: Option<SandboxConfig> = None;
         // Egress namespace guard; replaced with a live one for proxy mode below.
         // Held in the session so teardown runs when the session ends.
         let mut egress_guard = EgressGuard::inert();
-        let mut cmd = if let Some(ref sandbox_config) = self.config.sandbox_config {
+        // A profile the session selected nests inside the runner's config.
+        let sandbox_config = match config.sandbox_profile.as_deref() {
+            Some(profile) => Some(
+                self.config
+                    .sandbox_config
+                    .clone()
+                    .unwrap_or_default()
+                    .with_session_profile(profile)?,
+            ),
+            None => self.config.sandbox_config.clone(),
+        };
+        // Profile in effect, for the session info; None when unsandboxed.
+        let mut sandbox_profile = None;
+        // Resource caps of the session; the workspace may tighten them.
+        let mut resources = sandbox_config
+            .as_ref()
+            .map(|sandbox| sandbox.resources.clone())
+            .unwrap_or_default();
+        let mut cmd = if let Some(ref sandbox_config) = sandbox_config {
             if sandbox_config.enabled {
                 // Merge with workspace-specific config (can only add restrictions)
                 let mut effective_config = sandbox_config.with_workspace_config(&config.cwd);
+                resources = effective_config.resources.clone();
                 if !effective_config
                     .extra_rw_bind
                     .contains(&session_socket_dir_str)
@@ -674,13 +896,14 @@ impl PiSessionManager {
                             cmd.arg(arg);
                         }
 
-                        // Add Pi binary and args
-                        cmd.arg(&self.config.pi_binary);
+                        // Add harness binary and args
+                        cmd.arg(&harness_binary);
                         for arg in &pi_args {
                             cmd.arg(arg);
                         }
 
                         bwrap_pre_exec_config = Some(effective_config.clone());
+                        sandbox_profile = Some(effective_config.active_profile().to_string());
 
                         // Proxy mode: create the egress namespace now (the child
                         // joins it via setns in the pre-exec hook below). Inert
@@ -692,13 +915,13 @@ impl PiSessionManager {
                         info!(
                             "Sandboxing Pi session '{}' with profile '{}' ({} bwrap args)",
                             session_id,
-                            effective_config.profile,
+                            effective_config.active_profile(),
                             bwrap_args.len()
                         );
                         debug!(
                             "bwrap command: bwrap {} {} {:?}",
                             bwrap_args.join(" "),
-                            self.config.pi_binary.display(),
+                            harness_binary.display(),
                             pi_args
                         );
 
@@ -719,7 +942,7 @@ impl PiSessionManager {
                 }
             } else {
                 // Sandbox config exists but is disabled
-                let mut cmd = Command::new(&self.config.pi_binary);
+                let mut cmd = Command::new(&harness_binary);
                 for arg in &pi_args {
                     cmd.arg(arg);
                 }
@@ -728,7 +951,7 @@ impl PiSessionManager {
             }
         } else {
             // No sandbox config - run Pi directly
-            let mut cmd = Command::new(&self.config.pi_binary);
+            let mut cmd = Command::new(&harness_binary);
             for arg in &pi_args {
                 cmd.arg(arg);
             }
@@ -736,7 +959,8 @@ impl PiSessionManager {
             cmd
         };
 
-        // Set environment variables
+        // Set environment variables (the session's take precedence)
+        cmd.envs(&harness.env);
         cmd.envs(&config.env);
         if !config.env.contains_key("AGENT_BROWSER_SOCKET_DIR") {
             cmd.env("AGENT_BROWSER_SOCKET_DIR", &session_socket_dir_str);
@@ -748,6 +972,21 @@ impl PiSessionManager {
         if !config.env.contains_key("OQTO_SESSION_ID") {
             cmd.env("OQTO_SESSION_ID", &session_id);
         }
+        let artifact_watch = self.artifacts.as_ref().map(|store| {
+            Arc::new(
+                ArtifactWatch::new(
+                    Arc::clone(store),
+                    session_artifacts_dir(&config.cwd, &session_id),
+                    session_id.clone(),
+                )
+                .with_globs(config.cwd.clone(), &self.config.artifacts.globs),
+            )
+        });
+        if let Some(watch) = &artifact_watch
+            && !config.env.contains_key(ARTIFACTS_DIR_ENV)
+        {
+            cmd.env(ARTIFACTS_DIR_ENV, watch.dir());
+        }
 
         // Configure pipes
         cmd.stdin(std::process::Stdio::piped());
@@ -764,9 +1003,22 @@ impl PiSessionManager {
             .context("Failed to configure bwrap pre-exec hooks")?;
         }
 
+        // Caps were asked for, so the session does not start without them.
+        let cgroup = match &self.cgroups {
+            Some(cgroups) => {
+                let cgroup = cgroups
+                    .session(&session_id, &resources)
+                    .context("Failed to create the session's cgroup")?;
+                cgroup.attach(cmd.as_std_mut());
+                Some(Arc::new(cgroup))
+            }
+            None => None,
+        };
+
         // Spawn the process
         let mut child = cmd.spawn().context("Failed to spawn Pi process")?;
-        let pid = child.id().unwrap_or(0);
+        let child_pid = child.id();
+        let pid = child_pid.unwrap_or(0);
         info!(
             "Spawned Pi process for session '{}' (pid={})",
             session_id, pid
@@ -776,6 +1028,21 @@ impl PiSessionManager {
         let stdin = child.stdin.take().context("Failed to get stdin")?;
         let stdout = child.stdout.take().context("Failed to get stdout")?;
         let stderr = child.stderr.take();
+        let process = Arc::new(Mutex::new(child));
+        let closing = Arc::new(AtomicBool::new(false));
+        let crash_watch = self.crash_bundles.as_ref().map(|store| CrashWatch {
+            process: Arc::clone(&process),
+            closing: Arc::clone(&closing),
+            store: Arc::clone(store),
+            context: CrashContext::new(
+                &harness.name,
+                &harness_binary,
+                &config.cwd,
+                bwrap_pre_exec_config.is_some(),
+                child_pid,
+                &config.env,
+            ),
+        });
 
         // Create channels
         // Per-subscriber event distribution. Each subscriber (browser tab)
@@ -785,6 +1052,23 @@ impl PiSessionManager {
         let subscribers = EventSubscribers::new();
         let (cmd_tx, cmd_rx) = mpsc::channel::<PiSessionCommand>(32);
 
+        if let Some(cgroup) = &cgroup {
+            tokio::spawn(Self::oom_watch_task(
+                Arc::downgrade(cgroup),
+                subscribers.clone(),
+                session_id.clone(),
+                self.config.runner_id.clone(),
+            ));
+        }
+        if let Some(blocked) = egress_guard.take_blocked() {
+            tokio::spawn(Self::egress_blocked_task(
+                blocked,
+                subscribers.clone(),
+                session_id.clone(),
+                self.config.runner_id.clone(),
+            ));
+        }
+
         // Shared state for the session
         let state = Arc::new(RwLock::new(PiSessionState::Starting));
         let last_activity = Arc::new(RwLock::new(Instant::now()));
@@ -818,6 +1102,8 @@ impl PiSessionManager {
             Vec::new()
         };
         let message_buffer: MessageBuffer = Arc::new(RwLock::new(seed_messages));
+        let approvals = Arc::new(ApprovalGate::default());
+        let interjections: InterjectionQueue = Arc::new(Mutex::new(Vec::new()));
 
         // Spawn stdout reader task
         let reader_handle = {
@@ -836,6 +1122,12 @@ impl PiSessionManager {
             let active_model_for_reader = Arc::clone(&active_model);
 
             let runner_id = self.config.runner_id.clone();
+            let tool_limiter = ToolRateLimiter::new(&self.config.tool_rate_limits);
+            let tool_output = self.config.tool_output.clone();
+            let output_lint = OutputLinter::new(&self.config.output_lint);
+            let file_history = self.file_history.clone();
+            let approvals = Arc::clone(&approvals);
+            let interjections = Arc::clone(&interjections);
             tokio::spawn(async move {
                 Self::stdout_reader_task(
                     session_id,
@@ -854,6 +1146,15 @@ impl PiSessionManager {
                     msg_buf,
                     active_provider_for_reader,
                     active_model_for_reader,
+                    crash_watch,
+                    artifact_watch,
+                    file_history,
+                    tool_limiter,
+                    tool_output,
+                    output_lint,
+                    approval_policy,
+                    approvals,
+                    interjections,
                 )
                 .await;
             })
@@ -865,6 +1166,8 @@ impl PiSessionManager {
             let state = Arc::clone(&state);
             let last_activity = Arc::clone(&last_activity);
             let pending_client_id = Arc::clone(&pending_client_id);
+            let work_dir = config.cwd.clone();
+            let file_history = self.file_history.clone();
 
             tokio::spawn(async move {
                 Self::command_processor_task(
@@ -874,6 +1177,8 @@ impl PiSessionManager {
                     state,
                     last_activity,
                     pending_client_id,
+                    work_dir,
+                    file_history,
                 )
                 .await;
             })
@@ -887,8 +1192,12 @@ impl PiSessionManager {
         let session = PiSession {
             id: session_id.clone(),
             config,
-            process: child,
+            process,
+            pid: child_pid,
+            closing,
+            sandbox_profile,
             _egress_guard: egress_guard,
+            _cgroup: cgroup,
             state: Arc::clone(&state),
             session_external_id,
             active_provider,
@@ -900,6 +1209,9 @@ impl PiSessionManager {
             fork_txn,
             pending_client_id,
             message_buffer,
+            approvals,
+            steering: harness.steering,
+            interjections,
             _reader_handle: reader_handle,
             _cmd_handle: cmd_handle,
         };
@@ -1083,6 +1395,77 @@ impl PiSessionManager {
         .await
     }
 
+    /// Give a session guidance mid-turn. Harnesses that take steering
+    /// messages (and idle sessions) get it right away; otherwise it is held
+    /// until the running turn ends and then sent as the next prompt.
+    ///
+    /// Returns the interjection ID and how it is delivered.
+    pub async fn interject(
+        &self,
+        session_id: &str,
+        message: &str,
+        client_id: Option<String>,
+    ) -> Result<(String, InterjectionDelivery)> {
+        let resolved_id = self
+            .resolve_session_key(session_id)
+            .await
+            .ok_or_else(|| anyhow::anyhow!("Session '{}' not found", session_id))?;
+        let (steering, state, interjections, event_tx) = {
+            let sessions = self.sessions.read().await;
+            let session = sessions
+                .get(&resolved_id)
+                .ok_or_else(|| anyhow::anyhow!("Session '{}' not found", session_id))?;
+            (
+                session.steering,
+                Arc::clone(&session.state),
+                Arc::clone(&session.interjections),
+                session.subscribers.clone(),
+            )
+        };
+
+        let interjection_id = format!("int_{}", uuid::Uuid::new_v4().simple());
+        let delivery = {
+            // Check the state under the queue lock: the reader drains the
+            // queue after marking the session idle, so nothing queued here
+            // can miss the end of the turn.
+            let mut queue = interjections.lock().await;
+            if !steering && *state.read().await == PiSessionState::Streaming {
+                queue.push(QueuedInterjection {
+                    id: interjection_id.clone(),
+                    message: message.to_string(),
+                    client_id: client_id.clone(),
+                });
+                InterjectionDelivery::Queued
+            } else {
+                InterjectionDelivery::Live
+            }
+        };
+        if delivery == InterjectionDelivery::Live {
+            self.steer_with_client_id(&resolved_id, message, client_id.clone())
+                .await?;
+        }
+        debug!(
+            "Pi[{}] interjection {} {:?}",
+            resolved_id, interjection_id, delivery
+        );
+
+        event_tx
+            .publish(&CanonicalEvent {
+                session_id: resolved_id,
+                runner_id: self.config.runner_id.clone(),
+                ts: chrono::Utc::now().timestamp_millis(),
+                seq: None,
+                payload: EventPayload::AgentInterjection {
+                    interjection_id: interjection_id.clone(),
+                    message: message.to_string(),
+                    delivery,
+                    client_id,
+                },
+            })
+            .await;
+        Ok((interjection_id, delivery))
+    }
+
     /// Send a follow-up message to a session.
     pub async fn follow_up(&self, session_id: &str, message: &str) -> Result<()> {
         self.send_command(
@@ -1143,6 +1526,16 @@ impl PiSessionManager {
         Ok(session.subscribers.subscribe().await)
     }
 
+    /// OS PIDs of the agents working in `workspace`.
+    pub async fn process_ids_in(&self, workspace: &Path) -> Vec<u32> {
+        let sessions = self.sessions.read().await;
+        sessions
+            .values()
+            .filter(|s| s.config.cwd.starts_with(workspace))
+            .filter_map(|s| s.child_pid())
+            .collect()
+    }
+
     /// List all sessions.
     ///
     /// Returns the Oqto session ID (the key in the sessions map) as the
@@ -1159,6 +1552,8 @@ impl PiSessionManager {
             PathBuf,
             Arc<RwLock<Option<String>>>,
             Arc<RwLock<Option<String>>>,
+            Option<String>,
+            Option<String>,
         )> = {
             let sessions = self.sessions.read().await;
             let mut snaps = Vec::with_capacity(sessions.len());
@@ -1172,6 +1567,8 @@ impl PiSessionManager {
                     s.config.cwd.clone(),
                     Arc::clone(&s.active_provider),
                     Arc::clone(&s.active_model),
+                    s.config.harness.clone(),
+                    s.sandbox_profile.clone(),
                 ));
             }
             snaps
@@ -1187,6 +1584,8 @@ impl PiSessionManager {
             cwd,
             active_provider,
             active_model,
+            harness,
+            sandbox_profile,
         ) in snapshots
         {
             let provider = active_provider.read().await.clone();
@@ -1218,6 +1617,8 @@ impl PiSessionManager {
                 cwd,
                 provider,
                 model,
+                harness,
+                sandbox_profile,
             });
         }
 
@@ -2784,6 +3185,70 @@ impl PiSessionManager {
         .await
     }
 
+    /// Tool calls of a session waiting for approval, oldest first.
+    pub async fn list_tool_approvals(&self, session_id: &str) -> Result<Vec<ToolApproval>> {
+        let resolved_id = self
+            .resolve_session_key(session_id)
+            .await
+            .ok_or_else(|| anyhow::anyhow!("Session '{}' not found", session_id))?;
+        let approvals = {
+            let sessions = self.sessions.read().await;
+            let session = sessions
+                .get(&resolved_id)
+                .ok_or_else(|| anyhow::anyhow!("Session '{}' not found", session_id))?;
+            Arc::clone(&session.approvals)
+        };
+        Ok(approvals.list().await)
+    }
+
+    /// Approve or deny a tool call waiting for approval. `Ok(None)` when the
+    /// approval is not pending (already resolved or timed out).
+    pub async fn resolve_tool_approval(
+        &self,
+        session_id: &str,
+        approval_id: &str,
+        approved: bool,
+        reason: Option<String>,
+    ) -> Result<Option<ToolApproval>> {
+        let resolved_id = self
+            .resolve_session_key(session_id)
+            .await
+            .ok_or_else(|| anyhow::anyhow!("Session '{}' not found", session_id))?;
+        let (approvals, cmd_tx, event_tx) = {
+            let sessions = self.sessions.read().await;
+            let session = sessions
+                .get(&resolved_id)
+                .ok_or_else(|| anyhow::anyhow!("Session '{}' not found", session_id))?;
+            (
+                Arc::clone(&session.approvals),
+                session.cmd_tx.clone(),
+                session.subscribers.clone(),
+            )
+        };
+        let Some(approval) = approvals.take(approval_id).await else {
+            return Ok(None);
+        };
+        info!(
+            "Pi[{}] tool call {} ({}) {}",
+            resolved_id,
+            approval.tool_call_id,
+            approval.name,
+            if approved { "approved" } else { "denied" }
+        );
+        Self::send_approval_decision(
+            &resolved_id,
+            &self.config.runner_id,
+            &approval,
+            approved,
+            reason,
+            false,
+            &cmd_tx,
+            &event_tx,
+        )
+        .await?;
+        Ok(Some(approval))
+    }
+
     /// Close a session.
     pub async fn close_session(&self, session_id: &str) -> Result<()> {
         let resolved_id = self.resolve_session_key(session_id).await;
@@ -2829,6 +3294,14 @@ impl PiSessionManager {
                 .map(|session| session.cmd_tx.clone())
         };
 
+        // Mark the exit as intentional before the process goes away.
+        {
+            let sessions = self.sessions.read().await;
+            if let Some(session) = sessions.get(resolved_id) {
+                session.closing.store(true, Ordering::SeqCst);
+            }
+        }
+
         // Best-effort close signal (can be dropped if receiver is gone).
         if let Some(cmd_tx) = cmd_tx {
             let _ = cmd_tx.send(PiSessionCommand::Close).await;
@@ -2836,9 +3309,9 @@ impl PiSessionManager {
 
         // Remove from sessions map
         let mut sessions = self.sessions.write().await;
-        if let Some(mut session) = sessions.remove(resolved_id) {
+        if let Some(session) = sessions.remove(resolved_id) {
             // Kill the process if still running
-            let _ = session.process.kill().await;
+            let _ = session.process.lock().await.kill().await;
             info!("Session '{}' closed", resolved_id);
         }
 
@@ -3071,6 +3544,7 @@ impl PiSessionManager {
                             session_id: id.clone(),
                             runner_id: self.config.runner_id.clone(),
                             ts: chrono::Utc::now().timestamp_millis(),
+                            seq: None,
                             payload: EventPayload::AgentError {
                                 error: "Agent process died unexpectedly".to_string(),
                                 recoverable: false,
@@ -3082,6 +3556,7 @@ impl PiSessionManager {
                             session_id: id.clone(),
                             runner_id: self.config.runner_id.clone(),
                             ts: chrono::Utc::now().timestamp_millis(),
+                            seq: None,
                             payload: EventPayload::AgentIdle {
                                 message_version: None,
                             },
@@ -3109,6 +3584,7 @@ impl PiSessionManager {
                                 session_id: id.clone(),
                                 runner_id: self.config.runner_id.clone(),
                                 ts: chrono::Utc::now().timestamp_millis(),
+                                seq: None,
                                 payload: EventPayload::AgentIdle {
                                     message_version: None,
                                 },
@@ -3136,6 +3612,7 @@ impl PiSessionManager {
                             session_id: id.clone(),
                             runner_id: self.config.runner_id.clone(),
                             ts: chrono::Utc::now().timestamp_millis(),
+                            seq: None,
                             payload: EventPayload::AgentError {
                                 error: format!(
                                     "No response for {}s -- request timed out. The agent process was still alive but no data was received.",
@@ -3150,6 +3627,7 @@ impl PiSessionManager {
                             session_id: id.clone(),
                             runner_id: self.config.runner_id.clone(),
                             ts: chrono::Utc::now().timestamp_millis(),
+                            seq: None,
                             payload: EventPayload::AgentIdle {
                                 message_version: None,
                             },
@@ -3210,6 +3688,15 @@ impl PiSessionManager {
         message_buffer: MessageBuffer,
         active_provider: Arc<RwLock<Option<String>>>,
         active_model: Arc<RwLock<Option<String>>>,
+        crash_watch: Option<CrashWatch>,
+        artifact_watch: Option<Arc<ArtifactWatch>>,
+        file_history: Option<Arc<FileHistoryStore>>,
+        mut tool_limiter: Option<ToolRateLimiter>,
+        tool_output: ToolOutputConfig,
+        mut output_lint: Option<OutputLinter>,
+        approval_policy: Option<ToolApprovalPolicy>,
+        approvals: Arc<ApprovalGate>,
+        interjections: InterjectionQueue,
     ) {
         // Read stderr in a separate task, keeping last N lines in a ring buffer
         // so we can include them in the crash error event.
@@ -3249,7 +3736,7 @@ impl PiSessionManager {
         let mut pending_bound_client_ids: Vec<String> = Vec::new();
         let mut bridge_turn_bound_client_ids: VecDeque<String> = VecDeque::new();
         let mut retry_cycle_active = false;
-        let mut translator = PiTranslator::new();
+        let mut translator = PiTranslator::new().with_tool_output(&tool_output);
         let mut stream_trace_file = Self::open_stream_trace_file(&session_id).await;
 
         // Track the last session title broadcast to avoid redundant updates
@@ -3382,7 +3869,7 @@ impl PiSessionManager {
                 };
 
                 // Handle responses vs events
-                let pi_event = match msg {
+                let mut pi_event = match msg {
                     PiMessage::Event(e) => *e,
                     PiMessage::Response(response) => {
                         debug!("Pi[{}] response: {:?}", session_id, response);
@@ -3483,6 +3970,7 @@ impl PiSessionManager {
                                     session_id: session_id.clone(),
                                     runner_id: runner_id.clone(),
                                     ts: chrono::Utc::now().timestamp_millis(),
+                                    seq: None,
                                     payload:
                                         oqto_protocol::events::EventPayload::SessionTitleChanged {
                                             title: clean_title.clone(),
@@ -3533,6 +4021,61 @@ impl PiSessionManager {
                     bridge_turn_bound_client_ids.push_back(bound_client_id);
                 }
 
+                // The approval extension's questions are answered by the
+                // runner and never reach clients as input requests.
+                if let PiEvent::ExtensionUiRequest(req) = &pi_event
+                    && let Some(call) = ApprovalCall::parse(
+                        &req.method,
+                        req.title.as_deref(),
+                        req.placeholder.as_deref(),
+                    )
+                {
+                    Self::gate_tool_call(
+                        &session_id,
+                        &runner_id,
+                        &req.id,
+                        call,
+                        approval_policy.as_ref(),
+                        &approvals,
+                        &cmd_tx,
+                        &event_tx,
+                    )
+                    .await;
+                    continue;
+                }
+
+                // Lint finished assistant messages before they are stored or
+                // sent on. Blocks carry over to the turn's agent_end snapshot.
+                let mut lint_report = None;
+                if let Some(linter) = output_lint.as_mut() {
+                    match &mut pi_event {
+                        PiEvent::MessageStart { message } => linter.observe_prompt(message),
+                        PiEvent::MessageEnd { message } => {
+                            lint_report = linter.check(&session_id, message).await;
+                        }
+                        PiEvent::AgentEnd { messages } => linter.apply_withheld(messages),
+                        _ => {}
+                    }
+                }
+
+                // Snapshot the workspace after each turn; the snapshot before
+                // it is taken when the prompt is sent.
+                if let Some(store) = &file_history
+                    && matches!(pi_event, PiEvent::AgentEnd { .. })
+                {
+                    let store = Arc::clone(store);
+                    let work_dir = work_dir.clone();
+                    let session_id = session_id.clone();
+                    tokio::spawn(async move {
+                        if let Err(e) = store
+                            .snapshot(&work_dir, "after turn", Some(&session_id))
+                            .await
+                        {
+                            warn!("Pi[{}] workspace snapshot failed: {:#}", session_id, e);
+                        }
+                    });
+                }
+
                 // Update internal state based on Pi event
                 let new_state = match &pi_event {
                     PiEvent::AgentStart => {
@@ -3864,6 +4407,10 @@ impl PiSessionManager {
                 // Translate Pi event to canonical events and broadcast each one.
                 // For AgentEnd, oqto-log is already persisted above so the frontend
                 // can safely read history on agent.idle.
+                let lint_message_id = lint_report
+                    .as_ref()
+                    .and(translator.streaming_message_id())
+                    .map(str::to_string);
                 let canonical_payloads = translator.translate(&pi_event);
                 let ts = chrono::Utc::now().timestamp_millis();
                 for payload in &canonical_payloads {
@@ -4064,6 +4611,7 @@ impl PiSessionManager {
                         session_id: session_id.clone(),
                         runner_id: runner_id.clone(),
                         ts,
+                        seq: None,
                         payload: enriched_payload,
                     };
                     event_tx.publish(&canonical_event).await;
@@ -4077,6 +4625,139 @@ impl PiSessionManager {
                     }
                 }
 
+                if let Some(report) = lint_report
+                    && let Some(message_id) = lint_message_id
+                {
+                    if report.blocked {
+                        warn!(
+                            "Pi[{}] output lint blocked message {}",
+                            session_id, message_id
+                        );
+                    }
+                    event_tx
+                        .publish(&CanonicalEvent {
+                            session_id: session_id.clone(),
+                            runner_id: runner_id.clone(),
+                            ts: chrono::Utc::now().timestamp_millis(),
+                            seq: None,
+                            payload: EventPayload::StreamMessageLint {
+                                message_id,
+                                blocked: report.blocked,
+                                findings: report.findings,
+                            },
+                        })
+                        .await;
+                }
+
+                // Files the agent wrote to its artifacts directory during a
+                // tool call become `artifact.created` events. Idle is a
+                // second chance for files that were still being written, and
+                // collects workspace files matching the artifact globs.
+                if let Some(watch) = &artifact_watch {
+                    let trigger = canonical_payloads.iter().find_map(|payload| match payload {
+                        oqto_protocol::events::EventPayload::ToolEnd { tool_call_id, .. } => {
+                            Some(Some(tool_call_id.clone()))
+                        }
+                        oqto_protocol::events::EventPayload::AgentIdle { .. } => Some(None),
+                        _ => None,
+                    });
+                    if let Some(tool_call_id) = trigger {
+                        let watch = Arc::clone(watch);
+                        let event_tx = event_tx.clone();
+                        let session_id = session_id.clone();
+                        let runner_id = runner_id.clone();
+                        tokio::spawn(async move {
+                            let mut artifacts = watch.scan(tool_call_id.as_deref()).await;
+                            if tool_call_id.is_none() {
+                                artifacts.extend(watch.collect().await);
+                            }
+                            for artifact in artifacts {
+                                let event = CanonicalEvent {
+                                    session_id: session_id.clone(),
+                                    runner_id: runner_id.clone(),
+                                    ts: chrono::Utc::now().timestamp_millis(),
+                                    seq: None,
+                                    payload: oqto_protocol::events::EventPayload::ArtifactCreated {
+                                        artifact,
+                                    },
+                                };
+                                event_tx.publish(&event).await;
+                            }
+                        });
+                    }
+                }
+
+                // Tools already run by the time Pi reports them, so an agent
+                // over its limit is steered to back off and, if it keeps
+                // going, aborted.
+                if let Some(limiter) = tool_limiter.as_mut()
+                    && let PiEvent::ToolExecutionStart {
+                        tool_call_id,
+                        tool_name,
+                        ..
+                    } = &pi_event
+                    && let Some(hit) = limiter.check(tool_name, Instant::now())
+                {
+                    warn!(
+                        "Pi[{}] tool rate limit: {} ({}) over {} per {}s{}",
+                        session_id,
+                        tool_name,
+                        hit.rule,
+                        hit.max_calls,
+                        hit.window_secs,
+                        if hit.abort { ", aborting" } else { "" }
+                    );
+                    event_tx
+                        .publish(&CanonicalEvent {
+                            session_id: session_id.clone(),
+                            runner_id: runner_id.clone(),
+                            ts: chrono::Utc::now().timestamp_millis(),
+                            seq: None,
+                            payload: EventPayload::ToolRateLimited {
+                                tool_call_id: tool_call_id.clone(),
+                                name: tool_name.clone(),
+                                rule: hit.rule.clone(),
+                                max_calls: hit.max_calls,
+                                window_secs: hit.window_secs,
+                                retry_after_ms: hit.retry_after.as_millis() as u64,
+                                aborted: hit.abort,
+                            },
+                        })
+                        .await;
+                    let limit_cmd_tx = cmd_tx.clone();
+                    let hint = hit.send_hint.then(|| hit.hint(tool_name));
+                    let abort = hit.abort;
+                    tokio::spawn(async move {
+                        if let Some(message) = hint {
+                            let _ = limit_cmd_tx
+                                .send(PiSessionCommand::Steer {
+                                    message,
+                                    client_id: None,
+                                })
+                                .await;
+                        }
+                        if abort {
+                            let _ = limit_cmd_tx.send(PiSessionCommand::Abort).await;
+                        }
+                    });
+                }
+
+                // Hand interjections held during the turn to the agent as the
+                // next prompt. A retry cycle continues the same turn.
+                if matches!(pi_event, PiEvent::AgentEnd { .. }) && !retry_cycle_active {
+                    let queued = std::mem::take(&mut *interjections.lock().await);
+                    if !queued.is_empty() {
+                        Self::deliver_interjections(
+                            &session_id,
+                            &runner_id,
+                            queued,
+                            &cmd_tx,
+                            &event_tx,
+                        )
+                        .await;
+                    }
+                }
+
                 // Title updates primarily arrive via the auto-rename extension's
                 // setStatus("oqto_title_changed", name) which the translator
                 // converts to a SessionTitleChanged canonical event. As a
@@ -4122,17 +4803,183 @@ impl PiSessionManager {
             )
         };
 
+        if let Some(watch) = crash_watch {
+            let session_id = session_id.clone();
+            let pi_session_id = session_external_id.read().await.clone();
+            let work_dir = work_dir.clone();
+            tokio::spawn(async move {
+                Self::collect_crash_bundle(
+                    watch,
+                    session_id,
+                    pi_session_id,
+                    work_dir,
+                    stderr_lines,
+                )
+                .await;
+            });
+        }
+
         let exit_event = translator.state.on_process_exit(error_msg);
         let canonical_event = CanonicalEvent {
             session_id: session_id.clone(),
             runner_id,
             ts: chrono::Utc::now().timestamp_millis(),
+            seq: None,
             payload: exit_event,
         };
         event_tx.publish(&canonical_event).await;
         *state.write().await = PiSessionState::Stopping;
     }
 
+    /// Write a crash bundle if the process exited abnormally on its own.
+    async fn collect_crash_bundle(
+        watch: CrashWatch,
+        session_id: String,
+        pi_session_id: String,
+        work_dir: PathBuf,
+        stderr_lines: Vec<String>,
+    ) {
+        let Some(status) = watch.exit_status().await else {
+            debug!(
+                "Pi[{}] stdout closed but process has not exited; skipping crash bundle",
+                session_id
+            );
+            return;
+        };
+        if !is_abnormal_exit(&status) || watch.closing.load(Ordering::SeqCst) {
+            return;
+        }
+        warn!("Pi[{}] exited abnormally ({})", session_id, status);
+
+        let lookup_id = if pi_session_id.is_empty() {
+            session_id.clone()
+        } else {
+            pi_session_id.clone()
+        };
+        let session_file =
+            oqto_pi::session_files::find_session_file_async(lookup_id, Some(work_dir)).await;
+        if let Err(e) = watch
+            .store
+            .collect(
+                &session_id,
+                Some(pi_session_id.as_str()),
+                status,
+                stderr_lines,
+                session_file.as_deref(),
+                &watch.context,
+            )
+            .await
+        {
+            warn!("Pi[{}] failed to collect crash bundle: {:#}", session_id, e);
+        }
+    }
+
+    /// Background task that reports OOM kills in the session's cgroup as
+    /// `resource.oom_killed` events. Ends with the session.
+    async fn oom_watch_task(
+        cgroup: std::sync::Weak<SessionCgroup>,
+        subscribers: EventSubscribers,
+        session_id: String,
+        runner_id: String,
+    ) {
+        let mut reported = 0;
+        let mut interval = tokio::time::interval(OOM_WATCH_INTERVAL);
+        loop {
+            interval.tick().await;
+            let Some(cgroup) = cgroup.upgrade() else {
+                return;
+            };
+            let oom_kills = cgroup.oom_kills();
+            if oom_kills <= reported {
+                continue;
+            }
+            reported = oom_kills;
+            let memory_limit_mb = cgroup.resources().memory_mb;
+            warn!(
+                "Pi[{}] OOM killer killed {} process(es) at the {:?} MiB cap",
+                session_id, oom_kills, memory_limit_mb
+            );
+            let event = CanonicalEvent {
+                session_id: session_id.clone(),
+                runner_id: runner_id.clone(),
+                ts: chrono::Utc::now().timestamp_millis(),
+                seq: None,
+                payload: EventPayload::ResourceOomKilled {
+                    oom_kills,
+                    memory_limit_mb,
+                },
+            };
+            subscribers.publish(&event).await;
+        }
+    }
+
+    /// Publish the connections the egress relay of a session blocked, read
+    /// from its stdout until the relay exits.
+    async fn egress_blocked_task(
+        blocked: std::process::ChildStdout,
+        subscribers: EventSubscribers,
+        session_id: String,
+        runner_id: String,
+    ) {
+        let blocked = match tokio::process::ChildStdout::from_std(blocked) {
+            Ok(blocked) => blocked,
+            Err(e) => {
+                warn!("Pi[{}] cannot read egress relay reports: {}", session_id, e);
+                return;
+            }
+        };
+        let mut lines = BufReader::new(blocked).lines();
+        while let Ok(Some(line)) = lines.next_line().await {
+            let Ok(connection) = serde_json::from_str::<oqto_sandbox::BlockedConnection>(&line)
+            else {
+                continue;
+            };
+            let event = CanonicalEvent {
+                session_id: session_id.clone(),
+                runner_id: runner_id.clone(),
+                ts: chrono::Utc::now().timestamp_millis(),
+                seq: None,
+                payload: EventPayload::NetworkBlocked {
+                    host: connection.host,
+                    address: connection.address,
+                    port: connection.port,
+                    reason: connection.reason,
+                },
+            };
+            subscribers.publish(&event).await;
+        }
+    }
+
+    /// Snapshot the workspace before a turn starts, so the snapshot has none
+    /// of the turn's edits. Waits at most the store's snapshot timeout; a
+    /// slower snapshot finishes in the background while the turn runs.
+    async fn snapshot_before_turn(
+        store: &Arc<FileHistoryStore>,
+        work_dir: &Path,
+        session_id: &str,
+    ) {
+        let timeout = store.snapshot_timeout();
+        let snapshot = {
+            let store = Arc::clone(store);
+            let work_dir = work_dir.to_path_buf();
+            let session_id = session_id.to_string();
+            tokio::spawn(async move {
+                store
+                    .snapshot(&work_dir, "before turn", Some(&session_id))
+                    .await
+            })
+        };
+        match tokio::time::timeout(timeout, snapshot).await {
+            Ok(Ok(Ok(_))) => {}
+            Ok(Ok(Err(e))) => warn!("Pi[{}] workspace snapshot failed: {:#}", session_id, e),
+            Ok(Err(e)) => warn!("Pi[{}] workspace snapshot panicked: {}", session_id, e),
+            Err(_) => warn!(
+                "Pi[{}] workspace snapshot took over {:?}; starting the turn without waiting",
+                session_id, timeout
+            ),
+        }
+    }
+
     /// Background task that processes commands and writes to stdin.
     async fn command_processor_task(
         session_id: String,
@@ -4141,6 +4988,8 @@ impl PiSessionManager {
         state: Arc<RwLock<PiSessionState>>,
         last_activity: Arc<RwLock<Instant>>,
         pending_client_id: PendingClientId,
+        work_dir: PathBuf,
+        file_history: Option<Arc<FileHistoryStore>>,
     ) {
         while let Some(cmd) = cmd_rx.recv().await {
             let result = match cmd {
@@ -4156,6 +5005,11 @@ impl PiSessionManager {
                         *state.write().await = PiSessionState::Streaming;
                         None
                     };
+                    if streaming_behavior.is_none()
+                        && let Some(store) = &file_history
+                    {
+                        Self::snapshot_before_turn(store, &work_dir, &session_id).await;
+                    }
 
                     let outbound_message = Self::append_oqto_meta(
                         message,
@@ -4192,6 +5046,9 @@ impl PiSessionManager {
                             | PiSessionState::Aborting
                     ) {
                         *state.write().await = PiSessionState::Streaming;
+                        if let Some(store) = &file_history {
+                            Self::snapshot_before_turn(store, &work_dir, &session_id).await;
+                        }
                     }
 
                     let outbound_message =
@@ -4221,6 +5078,9 @@ impl PiSessionManager {
                             | PiSessionState::Aborting
                     ) {
                         *state.write().await = PiSessionState::Streaming;
+                        if let Some(store) = &file_history {
+                            Self::snapshot_before_turn(store, &work_dir, &session_id).await;
+                        }
                     }
 
                     let outbound_message =
@@ -4506,6 +5366,180 @@ impl PiSessionManager {
         Self::is_empty_assistant_placeholder(msg)
     }
 
+    /// Answer the approval extension about a tool call: right away when no
+    /// rule matches, otherwise once the user decides or the approval times
+    /// out (which denies the call).
+    async fn gate_tool_call(
+        session_id: &str,
+        runner_id: &str,
+        ui_request_id: &str,
+        call: ApprovalCall,
+        policy: Option<&ToolApprovalPolicy>,
+        approvals: &Arc<ApprovalGate>,
+        cmd_tx: &mpsc::Sender<PiSessionCommand>,
+        event_tx: &EventSubscribers,
+    ) {
+        let rule = policy.and_then(|p| Some((p.check(&call.tool_name, &call.input)?, p.timeout())));
+        let Some((rule, timeout)) = rule else {
+            let cmd_tx = cmd_tx.clone();
+            let id = ui_request_id.to_string();
+            tokio::spawn(async move {
+                let _ = cmd_tx
+                    .send(PiSessionCommand::ExtensionUiResponse {
+                        id,
+                        value: Some(decision_value(true, None)),
+                        confirmed: None,
+                        cancelled: None,
+                    })
+                    .await;
+            });
+            return;
+        };
+
+        let now = chrono::Utc::now().timestamp_millis();
+        let approval = ToolApproval {
+            approval_id: ui_request_id.to_string(),
+            tool_call_id: call.tool_call_id,
+            name: call.tool_name,
+            input: call.input,
+            rule: rule.to_string(),
+            requested_at: now,
+            expires_at: now + timeout.as_millis() as i64,
+        };
+        info!(
+            "Pi[{}] tool call {} ({}) waits for approval (rule {})",
+            session_id, approval.tool_call_id, approval.name, approval.rule
+        );
+        approvals.open(approval.clone()).await;
+        event_tx
+            .publish(&CanonicalEvent {
+                session_id: session_id.to_string(),
+                runner_id: runner_id.to_string(),
+                ts: now,
+                seq: None,
+                payload: EventPayload::ToolApprovalRequired {
+                    approval_id: approval.approval_id.clone(),
+                    tool_call_id: approval.tool_call_id.clone(),
+                    name: approval.name.clone(),
+                    input: approval.input.clone(),
+                    rule: approval.rule.clone(),
+                    expires_at: approval.expires_at,
+                },
+            })
+            .await;
+
+        let approvals = Arc::clone(approvals);
+        let cmd_tx = cmd_tx.clone();
+        let event_tx = event_tx.clone();
+        let session_id = session_id.to_string();
+        let runner_id = runner_id.to_string();
+        tokio::spawn(async move {
+            tokio::time::sleep(timeout).await;
+            if let Some(approval) = approvals.take(&approval.approval_id).await {
+                warn!(
+                    "Pi[{}] approval of tool call {} timed out, denying",
+                    session_id, approval.tool_call_id
+                );
+                let _ = Self::send_approval_decision(
+                    &session_id,
+                    &runner_id,
+                    &approval,
+                    false,
+                    None,
+                    true,
+                    &cmd_tx,
+                    &event_tx,
+                )
+                .await;
+            }
+        });
+    }
+
+    /// Pass a decision on to the approval extension and report it.
+    async fn send_approval_decision(
+        session_id: &str,
+        runner_id: &str,
+        approval: &ToolApproval,
+        approved: bool,
+        reason: Option<String>,
+        timed_out: bool,
+        cmd_tx: &mpsc::Sender<PiSessionCommand>,
+        event_tx: &EventSubscribers,
+    ) -> Result<()> {
+        cmd_tx
+            .send(PiSessionCommand::ExtensionUiResponse {
+                id: approval.approval_id.clone(),
+                value: Some(decision_value(approved, reason.as_deref())),
+                confirmed: None,
+                cancelled: None,
+            })
+            .await
+            .context("Failed to send approval decision to session")?;
+        event_tx
+            .publish(&CanonicalEvent {
+                session_id: session_id.to_string(),
+                runner_id: runner_id.to_string(),
+                ts: chrono::Utc::now().timestamp_millis(),
+                seq: None,
+                payload: EventPayload::ToolApprovalResolved {
+                    approval_id: approval.approval_id.clone(),
+                    tool_call_id: approval.tool_call_id.clone(),
+                    approved,
+                    reason,
+                    timed_out,
+                },
+            })
+            .await;
+        Ok(())
+    }
+
+    /// Send interjections queued during a turn as one prompt.
+    async fn deliver_interjections(
+        session_id: &str,
+        runner_id: &str,
+        queued: Vec<QueuedInterjection>,
+        cmd_tx: &mpsc::Sender<PiSessionCommand>,
+        event_tx: &EventSubscribers,
+    ) {
+        let client_id = match queued.as_slice() {
+            [only] => only.client_id.clone(),
+            _ => None,
+        };
+        let message = queued
+            .iter()
+            .map(|i| i.message.as_str())
+            .collect::<Vec<_>>()
+            .join("\n\n");
+        if cmd_tx
+            .send(PiSessionCommand::Prompt { message, client_id })
+            .await
+            .is_err()
+        {
+            warn!(
+                "Pi[{}] dropping {} queued interjection(s): session closed",
+                session_id,
+                queued.len()
+            );
+            return;
+        }
+        info!(
+            "Pi[{}] delivered {} queued interjection(s)",
+            session_id,
+            queued.len()
+        );
+        event_tx
+            .publish(&CanonicalEvent {
+                session_id: session_id.to_string(),
+                runner_id: runner_id.to_string(),
+                ts: chrono::Utc::now().timestamp_millis(),
+                seq: None,
+                payload: EventPayload::AgentInterjectionDelivered {
+                    interjection_ids: queued.into_iter().map(|i| i.id).collect(),
+                },
+            })
+            .await;
+    }
+
     fn parse_oqto_turn_bound_client_id(pi_event: &PiEvent) -> Option<String> {
         let PiEvent::ExtensionUiRequest(req) = pi_event else {
             return None;
@@ -18,7 +18,7 @@ use serde_json::Value;
 use oqto_protocol::Part;
 use oqto_protocol::events::{
     AgentPhase, CommandResponse, CompactReason, EventPayload, InputRequest, NotifyLevel,
-    ToolCallInfo,
+    OutputChannel, ToolCallInfo,
 };
 use oqto_protocol::messages::{Message, Role, StopReason, Usage};
 
@@ -28,6 +28,8 @@ use oqto_pi::{
 };
 use oqto_protocol::runner::SessionState;
 
+use crate::tool_output::{ToolOutputConfig, ToolOutputStreams};
+
 // ============================================================================
 // Translator
 // ============================================================================
@@ -62,6 +64,14 @@ pub struct PiTranslator {
     /// are suppressed to avoid flickering idle->working transitions on the
     /// frontend.
     in_retry_cycle: bool,
+
+    /// ID of the run in progress, from the agent_start that began it until
+    /// the agent goes idle (retries included).
+    run_id: Option<String>,
+
+    /// Turns tool progress snapshots into output deltas (None sends
+    /// `tool.progress` snapshots).
+    tool_output: Option<ToolOutputStreams>,
 }
 
 impl Default for PiTranslator {
@@ -79,15 +89,28 @@ impl PiTranslator {
             pending_client_id: None,
             streaming_occurred: false,
             in_retry_cycle: false,
+            run_id: None,
+            tool_output: None,
         }
     }
 
+    /// Stream running tools' text output as `tool.output_delta` events.
+    pub fn with_tool_output(mut self, config: &ToolOutputConfig) -> Self {
+        self.tool_output = ToolOutputStreams::new(config);
+        self
+    }
+
     /// Set the client ID for the next user message.
     /// Called by the runner before sending a prompt command.
     pub fn set_pending_client_id(&mut self, client_id: Option<String>) {
         self.pending_client_id = client_id;
     }
 
+    /// ID of the assistant message being streamed, if any.
+    pub fn streaming_message_id(&self) -> Option<&str> {
+        self.current_message_id.as_deref()
+    }
+
     /// Take and clear the pending client ID.
     fn take_pending_client_id(&mut self) -> Option<String> {
         self.pending_client_id.take()
@@ -172,10 +195,25 @@ impl PiTranslator {
             // already emitted AgentWorking(Retrying).
             return vec![];
         }
+        self.run_id
+            .get_or_insert_with(|| format!("run_{}", uuid::Uuid::new_v4().simple()));
         let event = self.state.on_agent_start();
         vec![event]
     }
 
+    /// Idle transition that ends the current run, followed by the run's
+    /// `agent.run_completed`.
+    fn end_run(&mut self) -> [EventPayload; 2] {
+        let run_id = self
+            .run_id
+            .take()
+            .unwrap_or_else(|| format!("run_{}", uuid::Uuid::new_v4().simple()));
+        [
+            self.state.on_agent_end(),
+            EventPayload::AgentRunCompleted { run_id },
+        ]
+    }
+
     fn on_agent_end(&mut self, messages: &[AgentMessage]) -> Vec<EventPayload> {
         if self.in_retry_cycle {
             // During retries, suppress the agent_end -> AgentIdle transition
@@ -260,8 +298,7 @@ impl PiTranslator {
         self.current_message_id = None;
 
         // Transition state.
-        let idle_event = self.state.on_agent_end();
-        events.push(idle_event);
+        events.extend(self.end_run());
 
         events
     }
@@ -485,6 +522,12 @@ impl PiTranslator {
         tool_name: &str,
         partial_result: &oqto_pi::ToolResult,
     ) -> Vec<EventPayload> {
+        if let Some(streams) = self.tool_output.as_mut()
+            && let Some(text) = result_text(partial_result)
+        {
+            // Pi's tools merge stderr into their text output.
+            return streams.update(tool_call_id, tool_name, OutputChannel::Stdout, &text);
+        }
         vec![EventPayload::ToolProgress {
             tool_call_id: tool_call_id.to_string(),
             name: tool_name.to_string(),
@@ -500,6 +543,9 @@ impl PiTranslator {
         is_error: bool,
     ) -> Vec<EventPayload> {
         let mut events = Vec::new();
+        if let Some(streams) = self.tool_output.as_mut() {
+            streams.finish(tool_call_id);
+        }
 
         // Back to generating after tool completes.
         let phase_event = self.state.on_native_phase(AgentPhase::Generating, None);
@@ -727,7 +773,7 @@ impl PiTranslator {
             // Clear streaming state and emit idle
             self.streaming_occurred = false;
             self.current_message_id = None;
-            events.push(self.state.on_agent_end());
+            events.extend(self.end_run());
         }
 
         events
@@ -979,6 +1025,19 @@ fn pi_stop_reason(reason: &str) -> StopReason {
     }
 }
 
+/// Text blocks of a tool result, or None when it has no text.
+fn result_text(result: &oqto_pi::ToolResult) -> Option<String> {
+    let texts: Vec<&str> = result
+        .content
+        .iter()
+        .filter_map(|block| match block {
+            ContentBlock::Text { text } => Some(text.as_str()),
+            _ => None,
+        })
+        .collect();
+    (!texts.is_empty()).then(|| texts.join("\n"))
+}
+
 /// Extract text content from a Pi message value.
 fn extract_text_content(content: &Value) -> Option<String> {
     match content {
@@ -1127,6 +1186,34 @@ mod tests {
         assert!(matches!(events[1], EventPayload::ToolEnd { .. }));
     }
 
+    #[test]
+    fn test_tool_progress_streams_deltas() {
+        let mut t = PiTranslator::new().with_tool_output(&ToolOutputConfig::default());
+        let update = |text: &str| PiEvent::ToolExecutionUpdate {
+            tool_call_id: "tc_1".to_string(),
+            tool_name: "bash".to_string(),
+            args: serde_json::json!({}),
+            partial_result: oqto_pi::ToolResult {
+                content: vec![ContentBlock::Text {
+                    text: text.to_string(),
+                }],
+                details: None,
+            },
+        };
+
+        t.translate(&update("test a ... ok\n"));
+        let events = t.translate(&update("test a ... ok\ntest b ... ok\n"));
+        assert_eq!(events.len(), 1);
+        assert!(matches!(
+            &events[0],
+            EventPayload::ToolOutputDelta { seq: 1, delta, .. } if delta == "test b ... ok\n"
+        ));
+
+        // Without streaming, snapshots are forwarded as tool.progress.
+        let events = PiTranslator::new().translate(&update("test a ... ok\n"));
+        assert!(matches!(events[0], EventPayload::ToolProgress { .. }));
+    }
+
     #[test]
     fn test_extension_oqto_phase() {
         let mut t = PiTranslator::new();
@@ -1278,6 +1365,61 @@ mod tests {
         ));
     }
 
+    #[test]
+    fn test_run_completed_once_per_run() {
+        let mut t = PiTranslator::new();
+        let completed = |events: &[EventPayload]| -> Vec<String> {
+            events
+                .iter()
+                .filter_map(|e| match e {
+                    EventPayload::AgentRunCompleted { run_id } => Some(run_id.clone()),
+                    _ => None,
+                })
+                .collect()
+        };
+
+        let mut runs = Vec::new();
+        for event in [
+            PiEvent::AgentStart,
+            PiEvent::AutoRetryStart {
+                attempt: 1,
+                max_attempts: 3,
+                delay_ms: 1000,
+                error_message: "rate limited".to_string(),
+            },
+            // The failed attempt's agent_end does not end the run.
+            PiEvent::AgentEnd { messages: vec![] },
+            PiEvent::AgentStart,
+            PiEvent::AutoRetryEnd {
+                success: true,
+                attempt: 1,
+                final_error: None,
+            },
+            PiEvent::AgentEnd { messages: vec![] },
+            PiEvent::AgentStart,
+            PiEvent::AgentEnd { messages: vec![] },
+        ] {
+            runs.extend(completed(&t.translate(&event)));
+        }
+        assert_eq!(runs.len(), 2);
+        assert_ne!(runs[0], runs[1]);
+
+        // Retries that run out end the run without an agent_end.
+        t.translate(&PiEvent::AgentStart);
+        t.translate(&PiEvent::AutoRetryStart {
+            attempt: 1,
+            max_attempts: 1,
+            delay_ms: 0,
+            error_message: "overloaded".to_string(),
+        });
+        let events = t.translate(&PiEvent::AutoRetryEnd {
+            success: false,
+            attempt: 1,
+            final_error: Some("overloaded".to_string()),
+        });
+        assert_eq!(completed(&events).len(), 1);
+    }
+
     #[test]
     fn test_hook_error() {
         let t = PiTranslator::new();
@@ -10,14 +10,39 @@
 //! - WriteStdin, ReadStdout, SubscribeStdout
 //!
 //! ### User-Plane Operations (for multi-user isolation)
-//! - Filesystem: ReadFile, WriteFile, ListDirectory, Stat, DeletePath
-//! - Sessions: ListSessions, GetSession, CreateSession, StopSession
+//! - Filesystem: ReadFile, WriteFile, ListDirectory, Stat, DeletePath, ExtractArchive
+//! - Sessions: ListSessions, GetSession, CreateSession, StopSession, GetSessionResources
 //! - Main Chat: ListMainChatSessions, GetMainChatMessages
 //! - Memory: SearchMemories, AddMemory, DeleteMemory
 //!
 //! ### Pi Session Management
-//! - PiCreateSession, PiPrompt, PiSteer, PiFollowUp, PiAbort, PiCompact
+//! - PiCreateSession, PiPrompt, PiSteer, PiFollowUp, PiInterject, PiAbort, PiCompact
 //! - PiSubscribe, PiUnsubscribe, PiListSessions, PiGetState, PiCloseSession, PiDeleteSession
+//!
+//! ### Harness Diagnostics
+//! - ListCrashBundles, GetCrashBundle
+//!
+//! ### Agent Artifacts
+//! - ListArtifacts, GetArtifact
+//!
+//! ### Background Processes
+//! - StartBackgroundProcess, ListBackgroundProcesses, StopBackgroundProcess
+//! - GetBackgroundProcessLogs
+//!
+//! ### Dependency Scans
+//! - ScanDependencies
+//!
+//! ### File History
+//! - ListFileVersions, ReadFileVersion
+//!
+//! ### Git
+//! - GitStatus, GitDiff, GitLog, GitBranches, GitSwitchBranch, GitCommit, GitPush
+//!
+//! ### Data Queries
+//! - QueryData
+//!
+//! ### Template Tests
+//! - RunSmokeCheck
 
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
@@ -90,6 +115,12 @@ pub enum RunnerRequest {
     /// Create a directory (with parents if needed).
     CreateDirectory(CreateDirectoryRequest),
 
+    /// Extract an uploaded archive into a new workspace directory.
+    ExtractArchive(ExtractArchiveRequest),
+
+    /// Query or change at-rest encryption of a workspace.
+    WorkspaceEncryption(WorkspaceEncryptionRequest),
+
     // ========================================================================
     // Session Operations (user-plane)
     // ========================================================================
@@ -105,6 +136,9 @@ pub enum RunnerRequest {
     /// Stop a running session.
     StopSession(StopSessionRequest),
 
+    /// Sample the CPU, memory and GPU usage of a session's processes.
+    GetSessionResources(GetSessionResourcesRequest),
+
     // ========================================================================
     // Main Chat Operations (user-plane)
     // ========================================================================
@@ -128,6 +162,9 @@ pub enum RunnerRequest {
     /// Repair missing workspace chat session metadata by scanning Pi JSONL session files.
     RepairWorkspaceChatHistory(RepairWorkspaceChatHistoryRequest),
 
+    /// Run an SQLite integrity check on the user's hstry database.
+    CheckHistoryIntegrity(CheckHistoryIntegrityRequest),
+
     // ========================================================================
     // Memory Operations (user-plane)
     // ========================================================================
@@ -194,6 +231,10 @@ pub enum RunnerRequest {
     /// Queue a follow-up message for after the Pi session finishes.
     PiFollowUp(PiFollowUpRequest),
 
+    /// Give a session guidance mid-turn (steered live or queued for the
+    /// next turn, depending on the harness).
+    PiInterject(PiInterjectRequest),
+
     /// Abort the current Pi session operation.
     PiAbort(PiAbortRequest),
 
@@ -298,6 +339,101 @@ pub enum RunnerRequest {
     // ========================================================================
     /// Send response to an extension UI request.
     PiExtensionUiResponse(PiExtensionUiResponseRequest),
+
+    // ========================================================================
+    // Tool Approvals
+    // ========================================================================
+    /// List a session's tool calls waiting for approval.
+    ListToolApprovals(ListToolApprovalsRequest),
+
+    /// Approve or deny a tool call waiting for approval.
+    ResolveToolApproval(ResolveToolApprovalRequest),
+
+    // ========================================================================
+    // Harness Diagnostics
+    // ========================================================================
+    /// List crash bundles collected after abnormal harness exits.
+    ListCrashBundles(ListCrashBundlesRequest),
+
+    /// Get a complete crash bundle.
+    GetCrashBundle(GetCrashBundleRequest),
+
+    // ========================================================================
+    // Agent Artifacts
+    // ========================================================================
+    /// List images captured from a session's artifacts directory.
+    ListArtifacts(ListArtifactsRequest),
+
+    /// Get a captured artifact's content.
+    GetArtifact(GetArtifactRequest),
+
+    // ========================================================================
+    // Background Processes
+    // ========================================================================
+    /// Start a supervised long-running process (dev server, watcher) for a
+    /// session.
+    StartBackgroundProcess(StartBackgroundProcessRequest),
+
+    /// List background processes, optionally for one session.
+    ListBackgroundProcesses(ListBackgroundProcessesRequest),
+
+    /// Stop a background process.
+    StopBackgroundProcess(StopBackgroundProcessRequest),
+
+    /// Get the recent output of a background process.
+    GetBackgroundProcessLogs(GetBackgroundProcessLogsRequest),
+
+    // ========================================================================
+    // Dependency Scans
+    // ========================================================================
+    /// Run vulnerability scanners over a workspace's dependency lockfiles.
+    ScanDependencies(ScanDependenciesRequest),
+
+    // ========================================================================
+    // File History
+    // ========================================================================
+    /// List earlier versions of a workspace file (turn snapshots and the
+    /// workspace's own git history).
+    ListFileVersions(ListFileVersionsRequest),
+
+    /// Read a workspace file as it was at a version or point in time.
+    ReadFileVersion(ReadFileVersionRequest),
+
+    // ========================================================================
+    // Git
+    // ========================================================================
+    /// Branch and changed files of a workspace repository.
+    GitStatus(GitStatusRequest),
+
+    /// Diff of uncommitted changes or of one commit.
+    GitDiff(GitDiffRequest),
+
+    /// Commits reachable from HEAD (or a revision), newest first.
+    GitLog(GitLogRequest),
+
+    /// Local branches.
+    GitBranches(GitBranchesRequest),
+
+    /// Check out a branch, optionally creating it.
+    GitSwitchBranch(GitSwitchBranchRequest),
+
+    /// Stage and commit changes.
+    GitCommit(GitCommitRequest),
+
+    /// Push a branch to a remote.
+    GitPush(GitPushRequest),
+
+    // ========================================================================
+    // Data Queries
+    // ========================================================================
+    /// Run a read-only SQL query over data files in a workspace.
+    QueryData(DataQueryRequest),
+
+    // ========================================================================
+    // Template Tests
+    // ========================================================================
+    /// Run a template's smoke check in a project created from it.
+    RunSmokeCheck(SmokeCheckRequest),
 }
 
 /// Response from runner to oqto.
@@ -364,6 +500,12 @@ pub enum RunnerResponse {
     /// Directory created successfully.
     DirectoryCreated(DirectoryCreatedResponse),
 
+    /// Archive extracted into a new directory.
+    ArchiveExtracted(ArchiveExtractedResponse),
+
+    /// Workspace encryption state.
+    WorkspaceEncryption(WorkspaceEncryptionResponse),
+
     // ========================================================================
     // Session Responses
     // ========================================================================
@@ -379,6 +521,9 @@ pub enum RunnerResponse {
     /// Session stopped.
     SessionStopped(SessionStoppedResponse),
 
+    /// Resource usage of a session's processes.
+    SessionResources(SessionResourcesResponse),
+
     // ========================================================================
     // Main Chat Responses
     // ========================================================================
@@ -405,6 +550,9 @@ pub enum RunnerResponse {
     /// Workspace chat history repair result.
     WorkspaceChatHistoryRepaired(WorkspaceChatHistoryRepairResponse),
 
+    /// hstry database integrity check result.
+    HistoryIntegrity(HistoryIntegrityResponse),
+
     // ========================================================================
     // Memory Responses
     // ========================================================================
@@ -459,6 +607,9 @@ pub enum RunnerResponse {
         session_id: String,
     },
 
+    /// Interjection accepted.
+    PiInterjected(PiInterjectedResponse),
+
     /// Pi subscription started.
     PiSubscribed(PiSubscribedResponse),
 
@@ -501,6 +652,93 @@ pub enum RunnerResponse {
     /// Pi export HTML result response.
     PiExportHtmlResult(PiExportHtmlResultResponse),
 
+    // ========================================================================
+    // Tool Approval Responses
+    // ========================================================================
+    /// Tool calls waiting for approval, oldest first.
+    ToolApprovalList(ToolApprovalListResponse),
+
+    /// The approval that was resolved.
+    ToolApprovalResolved(ToolApproval),
+
+    // ========================================================================
+    // Harness Diagnostics Responses
+    // ========================================================================
+    /// Crash bundle summaries, newest first.
+    CrashBundleList(CrashBundleListResponse),
+
+    /// A complete crash bundle.
+    CrashBundle(Box<CrashBundle>),
+
+    // ========================================================================
+    // Agent Artifacts Responses
+    // ========================================================================
+    /// Artifacts of a session, oldest first.
+    ArtifactList(ArtifactListResponse),
+
+    /// Artifact content.
+    Artifact(Box<ArtifactResponse>),
+
+    // ========================================================================
+    // Background Process Responses
+    // ========================================================================
+    /// A background process (after start or stop).
+    BackgroundProcess(BackgroundProcessInfo),
+
+    /// Background processes, oldest first.
+    BackgroundProcessList(BackgroundProcessListResponse),
+
+    /// Recent output of a background process.
+    BackgroundProcessLogs(BackgroundProcessLogsResponse),
+
+    // ========================================================================
+    // Dependency Scan Responses
+    // ========================================================================
+    /// Raw output of each scanner that ran.
+    DependencyScan(DependencyScanResponse),
+
+    // ========================================================================
+    // File History Responses
+    // ========================================================================
+    /// Versions of a file, newest first.
+    FileVersions(FileVersionsResponse),
+
+    /// Content of a file at a version.
+    FileVersionContent(FileVersionContentResponse),
+
+    // ========================================================================
+    // Git Responses
+    // ========================================================================
+    /// Status of a workspace repository.
+    GitStatus(GitStatusResponse),
+
+    /// A unified diff.
+    GitDiff(GitDiffResponse),
+
+    /// Commits, newest first.
+    GitLog(GitLogResponse),
+
+    /// Local branches (also the response to `GitSwitchBranch`).
+    GitBranches(GitBranchesResponse),
+
+    /// The commit that was created.
+    GitCommitted(GitCommitInfo),
+
+    /// Result of a push.
+    GitPushed(GitPushResponse),
+
+    // ========================================================================
+    // Data Query Responses
+    // ========================================================================
+    /// Columns and rows of a query.
+    DataQuery(DataQueryResponse),
+
+    // ========================================================================
+    // Template Test Responses
+    // ========================================================================
+    /// Outcome of a smoke check.
+    SmokeCheck(SmokeCheckResponse),
+
     // ========================================================================
     // Generic
     // ========================================================================
@@ -666,6 +904,94 @@ fn default_true() -> bool {
     true
 }
 
+/// Archive formats `ExtractArchive` accepts.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ArchiveFormat {
+    Zip,
+    TarGz,
+}
+
+impl ArchiveFormat {
+    /// Recognize an archive by its leading bytes.
+    pub fn detect(data: &[u8]) -> Option<Self> {
+        if data.starts_with(b"PK\x03\x04") || data.starts_with(b"PK\x05\x06") {
+            Some(Self::Zip)
+        } else if data.starts_with(&[0x1f, 0x8b]) {
+            Some(Self::TarGz)
+        } else {
+            None
+        }
+    }
+}
+
+/// Request to extract an archive into a directory that does not exist yet.
+/// Unsafe entries are skipped; exceeding a limit fails the whole request.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExtractArchiveRequest {
+    pub path: PathBuf,
+    pub format: ArchiveFormat,
+    /// Archive content (base64 encoded).
+    pub content_base64: String,
+    /// Most bytes the extracted files may hold.
+    pub max_bytes: u64,
+    /// Most files the archive may hold.
+    pub max_files: u64,
+}
+
+/// Secret passphrase that is redacted from `Debug` output.
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct Passphrase(String);
+
+impl Passphrase {
+    pub fn new(passphrase: impl Into<String>) -> Self {
+        Self(passphrase.into())
+    }
+
+    /// The raw passphrase. Only pass this to the process that needs it.
+    pub fn expose(&self) -> &str {
+        &self.0
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.chars().count()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+impl std::fmt::Debug for Passphrase {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("Passphrase(<redacted>)")
+    }
+}
+
+/// Request to query or change at-rest encryption of a workspace.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WorkspaceEncryptionRequest {
+    /// Workspace root directory.
+    pub workspace_path: PathBuf,
+    /// Operation to perform.
+    pub action: WorkspaceEncryptionAction,
+}
+
+/// Workspace encryption operations.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "action", rename_all = "snake_case")]
+pub enum WorkspaceEncryptionAction {
+    /// Report the current state.
+    Status,
+    /// Encrypt the workspace (migrating existing files) and leave it unlocked.
+    Enable { passphrase: Passphrase },
+    /// Mount the decrypted view.
+    Unlock { passphrase: Passphrase },
+    /// Unmount the decrypted view.
+    Lock,
+}
+
 // ============================================================================
 // Session Request Types
 // ============================================================================
@@ -696,6 +1022,61 @@ pub struct StartSessionRequest {
     /// Additional environment variables.
     #[serde(default)]
     pub env: HashMap<String, String>,
+    /// Secret the fileserver checks capability tokens against. Passed to the
+    /// fileserver only, never to the agent.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fileserver_token_secret: Option<String>,
+    /// Shell environment the workspace declares, applied to the terminal and
+    /// to agents started in the workspace.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub shell: Option<ShellEnvironment>,
+    /// The user's secrets for the session, kept out of the workspace.
+    #[serde(default, skip_serializing_if = "SessionSecrets::is_empty")]
+    pub secrets: SessionSecrets,
+}
+
+/// Secrets injected into a session. Agents and the terminal get the
+/// variables; the files are written to a private directory outside the
+/// workspace that `OQTO_SECRETS_DIR` points at, and removed when the session
+/// stops. `Debug` shows the names only.
+#[derive(Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct SessionSecrets {
+    /// Environment variables.
+    #[serde(default)]
+    pub env: std::collections::BTreeMap<String, String>,
+    /// File contents by file name.
+    #[serde(default)]
+    pub files: std::collections::BTreeMap<String, String>,
+}
+
+impl SessionSecrets {
+    pub fn is_empty(&self) -> bool {
+        self.env.is_empty() && self.files.is_empty()
+    }
+}
+
+impl std::fmt::Debug for SessionSecrets {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SessionSecrets")
+            .field("env", &self.env.keys().collect::<Vec<_>>())
+            .field("files", &self.files.keys().collect::<Vec<_>>())
+            .finish()
+    }
+}
+
+/// Shell environment declared by a workspace (`[shell]` in
+/// `.oqto/config.toml`), resolved by oqto.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ShellEnvironment {
+    /// Absolute directories prepended to `PATH`, first wins.
+    #[serde(default)]
+    pub path: Vec<PathBuf>,
+    /// Environment variables.
+    #[serde(default)]
+    pub env: std::collections::BTreeMap<String, String>,
+    /// Aliases defined in interactive terminals.
+    #[serde(default)]
+    pub aliases: std::collections::BTreeMap<String, String>,
 }
 
 /// Request to stop a session.
@@ -705,6 +1086,13 @@ pub struct StopSessionRequest {
     pub session_id: String,
 }
 
+/// Request for the resource usage of a session.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GetSessionResourcesRequest {
+    /// Session ID.
+    pub session_id: String,
+}
+
 // ============================================================================
 // Main Chat Request Types
 // ============================================================================
@@ -801,6 +1189,24 @@ pub struct RepairWorkspaceChatHistoryRequest {
     pub workspace: Option<String>,
 }
 
+/// Request to check the integrity of the user's hstry database.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CheckHistoryIntegrityRequest {
+    /// Run the full `integrity_check` instead of `quick_check`.
+    #[serde(default)]
+    pub full: bool,
+    /// Snapshot the database into its backup directory when the check passes.
+    #[serde(default)]
+    pub backup: bool,
+    /// Number of snapshots to keep when `backup` is set.
+    #[serde(default = "default_history_backups")]
+    pub keep_backups: usize,
+}
+
+fn default_history_backups() -> usize {
+    5
+}
+
 // ============================================================================
 // Memory Request Types
 // ============================================================================
@@ -944,6 +1350,14 @@ pub struct PiSessionConfig {
     /// Environment variables for the Pi process.
     #[serde(default)]
     pub env: HashMap<String, String>,
+    /// Harness to spawn, from the runner's harness registry (None = Pi).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub harness: Option<String>,
+    /// Sandbox profile ("net-off", "read-only-home", "full" or one from
+    /// sandbox.toml), nested inside the runner's sandbox config so it can
+    /// only add restrictions (None = the config as it is).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sandbox_profile: Option<String>,
 }
 
 impl Default for PiSessionConfig {
@@ -955,6 +1369,8 @@ impl Default for PiSessionConfig {
             session_file: None,
             continue_session: None,
             env: HashMap::new(),
+            harness: None,
+            sandbox_profile: None,
         }
     }
 }
@@ -1066,6 +1482,18 @@ pub struct PiFollowUpRequest {
     pub client_id: Option<String>,
 }
 
+/// Request to interject guidance into a Pi session.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PiInterjectRequest {
+    /// Session ID.
+    pub session_id: String,
+    /// Guidance for the agent.
+    pub message: String,
+    /// Client-generated ID for optimistic message matching.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub client_id: Option<String>,
+}
+
 /// Request to get all messages from a Pi session.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct PiGetMessagesRequest {
@@ -1227,27 +1655,341 @@ pub struct PiBashRequest {
 
 /// Request to abort a running bash command.
 #[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct PiAbortBashRequest {
-    /// Session ID.
-    pub session_id: String,
+pub struct PiAbortBashRequest {
+    /// Session ID.
+    pub session_id: String,
+}
+
+/// Request to respond to an extension UI prompt.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PiExtensionUiResponseRequest {
+    /// Session ID.
+    pub session_id: String,
+    /// Request ID from the extension_ui_request event.
+    pub id: String,
+    /// Value for select/input/editor responses.
+    #[serde(default)]
+    pub value: Option<String>,
+    /// Confirmation for confirm responses.
+    #[serde(default)]
+    pub confirmed: Option<bool>,
+    /// Whether the dialog was cancelled.
+    #[serde(default)]
+    pub cancelled: Option<bool>,
+}
+
+// ============================================================================
+// Tool Approval Request Types
+// ============================================================================
+
+/// Request for the tool calls of a session waiting for approval.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ListToolApprovalsRequest {
+    pub session_id: String,
+}
+
+/// Request to approve or deny a tool call.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ResolveToolApprovalRequest {
+    pub session_id: String,
+    pub approval_id: String,
+    pub approved: bool,
+    /// Passed on to the agent with a denial.
+    #[serde(default)]
+    pub reason: Option<String>,
+}
+
+// ============================================================================
+// Harness Diagnostics Request Types
+// ============================================================================
+
+/// Request to list crash bundles.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ListCrashBundlesRequest {
+    /// Only bundles for this session (runner session ID or Pi native ID).
+    #[serde(default)]
+    pub session_id: Option<String>,
+}
+
+/// Request to fetch one crash bundle.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GetCrashBundleRequest {
+    /// Bundle ID from [`CrashBundleSummary::id`].
+    pub bundle_id: String,
+}
+
+// ============================================================================
+// Agent Artifacts Request Types
+// ============================================================================
+
+/// Request to list a session's artifacts.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ListArtifactsRequest {
+    pub session_id: String,
+}
+
+/// Request to fetch an artifact.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GetArtifactRequest {
+    pub artifact_id: String,
+    /// Return the PNG thumbnail instead of the original (falls back to the
+    /// original when there is none, e.g. for SVGs).
+    #[serde(default)]
+    pub thumbnail: bool,
+}
+
+// ============================================================================
+// Background Process Request Types
+// ============================================================================
+
+/// Request to start a background process.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StartBackgroundProcessRequest {
+    /// Session that owns the process; it is stopped when the session closes.
+    pub session_id: String,
+    /// Display name (defaults to the program name).
+    #[serde(default)]
+    pub name: Option<String>,
+    /// Program and arguments.
+    pub command: Vec<String>,
+    /// Working directory (defaults to the session's working directory).
+    #[serde(default)]
+    pub cwd: Option<PathBuf>,
+    #[serde(default)]
+    pub env: HashMap<String, String>,
+    #[serde(default)]
+    pub restart: RestartPolicy,
+}
+
+/// Request to list background processes.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ListBackgroundProcessesRequest {
+    #[serde(default)]
+    pub session_id: Option<String>,
+}
+
+/// Request to stop a background process.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StopBackgroundProcessRequest {
+    pub id: String,
+}
+
+/// Request for a background process's output.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GetBackgroundProcessLogsRequest {
+    pub id: String,
+    /// Most recent lines to return (default 200).
+    #[serde(default)]
+    pub lines: Option<usize>,
+}
+
+// ============================================================================
+// Dependency Scan Request Types
+// ============================================================================
+
+/// A vulnerability scanner the runner knows how to invoke.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum DependencyScanner {
+    /// `osv-scanner` over every supported lockfile (recursive).
+    OsvScanner,
+    /// `cargo audit` for a root `Cargo.lock`.
+    CargoAudit,
+    /// `npm audit` for a root `package-lock.json`.
+    NpmAudit,
+}
+
+/// Request to scan a workspace's dependencies.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ScanDependenciesRequest {
+    pub workspace_path: PathBuf,
+    /// Scanners to try, in order. Ones whose binary or lockfile is missing
+    /// are reported as skipped.
+    pub scanners: Vec<DependencyScanner>,
+    /// Per-scanner time limit (default 300s).
+    #[serde(default)]
+    pub timeout_secs: Option<u64>,
+}
+
+// ============================================================================
+// File History Request Types
+// ============================================================================
+
+/// Request to list the versions of a workspace file.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ListFileVersionsRequest {
+    pub workspace_path: PathBuf,
+    /// File path relative to the workspace.
+    pub path: String,
+    /// Most versions returned (default 50).
+    #[serde(default)]
+    pub limit: Option<usize>,
+}
+
+/// Which version of a file to read.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kind", content = "value", rename_all = "snake_case")]
+pub enum FileVersionSelector {
+    /// A version `id` from `ListFileVersions` (or a unique prefix of the
+    /// commit hash).
+    Version(String),
+    /// The newest version at or before this time (Unix milliseconds).
+    At(i64),
+}
+
+/// Request to read a workspace file at an earlier version.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReadFileVersionRequest {
+    pub workspace_path: PathBuf,
+    /// File path relative to the workspace.
+    pub path: String,
+    pub selector: FileVersionSelector,
+}
+
+// ============================================================================
+// Git Request Types
+// ============================================================================
+
+/// Request for the status of a workspace repository.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GitStatusRequest {
+    pub workspace_path: PathBuf,
+}
+
+/// Request for a diff. Without `commit`, the uncommitted changes against
+/// HEAD (only the staged ones with `staged`).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GitDiffRequest {
+    pub workspace_path: PathBuf,
+    /// Limit the diff to this path, relative to the workspace.
+    #[serde(default)]
+    pub path: Option<String>,
+    #[serde(default)]
+    pub staged: bool,
+    /// Show the changes made by this commit instead.
+    #[serde(default)]
+    pub commit: Option<String>,
+}
+
+/// Request for the commit log.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GitLogRequest {
+    pub workspace_path: PathBuf,
+    /// Most commits returned (default 50).
+    #[serde(default)]
+    pub limit: Option<usize>,
+    /// Only commits touching this path, relative to the workspace.
+    #[serde(default)]
+    pub path: Option<String>,
+    /// Start from this revision instead of HEAD.
+    #[serde(default)]
+    pub rev: Option<String>,
+}
+
+/// Request for the local branches.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GitBranchesRequest {
+    pub workspace_path: PathBuf,
+}
+
+/// Request to check out a branch.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GitSwitchBranchRequest {
+    pub workspace_path: PathBuf,
+    pub name: String,
+    /// Create the branch from HEAD first.
+    #[serde(default)]
+    pub create: bool,
+}
+
+/// Request to commit changes.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GitCommitRequest {
+    pub workspace_path: PathBuf,
+    pub message: String,
+    /// Paths to stage and commit, relative to the workspace. None commits
+    /// every change, untracked files included.
+    #[serde(default)]
+    pub paths: Option<Vec<String>>,
+    /// Author; the repository's git config applies when unset.
+    #[serde(default)]
+    pub author_name: Option<String>,
+    #[serde(default)]
+    pub author_email: Option<String>,
+}
+
+/// HTTPS credentials for pushing to a host.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct GitCredential {
+    /// Host (and port, if not the default) of the remote URL.
+    pub host: String,
+    pub username: String,
+    /// Password or access token.
+    pub password: String,
+}
+
+impl std::fmt::Debug for GitCredential {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("GitCredential")
+            .field("host", &self.host)
+            .field("username", &self.username)
+            .field("password", &"<redacted>")
+            .finish()
+    }
+}
+
+/// Request to push a branch.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GitPushRequest {
+    pub workspace_path: PathBuf,
+    /// Remote to push to; defaults to the branch's upstream remote, then
+    /// `origin`.
+    #[serde(default)]
+    pub remote: Option<String>,
+    /// Branch to push; defaults to the current one.
+    #[serde(default)]
+    pub branch: Option<String>,
+    /// Make the remote branch the upstream of the local one.
+    #[serde(default)]
+    pub set_upstream: bool,
+    /// Credentials to offer; the one for the remote's host is used. Without
+    /// a match, the workspace user's own git credentials apply.
+    #[serde(default)]
+    pub credentials: Vec<GitCredential>,
 }
 
-/// Request to respond to an extension UI prompt.
+// ============================================================================
+// Data Query Request Types
+// ============================================================================
+
+/// Request to run a SQL query over files in a workspace. Relative paths in
+/// the query resolve against the workspace.
 #[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct PiExtensionUiResponseRequest {
-    /// Session ID.
-    pub session_id: String,
-    /// Request ID from the extension_ui_request event.
-    pub id: String,
-    /// Value for select/input/editor responses.
+pub struct DataQueryRequest {
+    pub workspace_path: PathBuf,
+    /// A single query (`SELECT`, `WITH`, `FROM`, ...).
+    pub sql: String,
+    /// Most rows returned (default 1000).
     #[serde(default)]
-    pub value: Option<String>,
-    /// Confirmation for confirm responses.
+    pub max_rows: Option<usize>,
+    /// Time limit (default 30s).
     #[serde(default)]
-    pub confirmed: Option<bool>,
-    /// Whether the dialog was cancelled.
+    pub timeout_secs: Option<u64>,
+}
+
+// ============================================================================
+// Template Test Request Types
+// ============================================================================
+
+/// Request to run a shell command in a project directory.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SmokeCheckRequest {
+    pub workspace_path: PathBuf,
+    /// Run with `sh -c`.
+    pub command: String,
+    /// Time limit (default 300s).
     #[serde(default)]
-    pub cancelled: Option<bool>,
+    pub timeout_secs: Option<u64>,
 }
 
 // ============================================================================
@@ -1463,6 +2205,42 @@ pub struct DirectoryCreatedResponse {
     pub path: PathBuf,
 }
 
+/// Response after extracting an archive.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ArchiveExtractedResponse {
+    /// Directory that was created.
+    pub path: PathBuf,
+    pub files: u64,
+    pub bytes: u64,
+    /// Entries skipped for unsafe paths or unsupported types (links,
+    /// devices).
+    pub skipped: u64,
+    /// Project files found at the top of the directory (`package.json`,
+    /// `.oqto/workspace.toml`, ...).
+    pub manifests: Vec<String>,
+}
+
+/// At-rest encryption state of a workspace.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum WorkspaceEncryptionState {
+    /// Workspace is stored in plaintext.
+    Disabled,
+    /// Encrypted and not mounted; files are inaccessible.
+    Locked,
+    /// Encrypted and mounted for the owning user.
+    Unlocked,
+}
+
+/// Response describing a workspace's encryption state.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WorkspaceEncryptionResponse {
+    /// Workspace root directory.
+    pub workspace_path: PathBuf,
+    /// State after the requested operation.
+    pub state: WorkspaceEncryptionState,
+}
+
 // ============================================================================
 // Session Response Types
 // ============================================================================
@@ -1522,6 +2300,27 @@ pub struct SessionStoppedResponse {
     pub session_id: String,
 }
 
+/// Resource usage of the processes of a session: the fileserver, terminal,
+/// background processes and agents in its workspace, with all their
+/// descendants.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SessionResourcesResponse {
+    /// Session ID.
+    pub session_id: String,
+    /// When the sample was taken (Unix ms).
+    pub sampled_at: i64,
+    /// CPU time used so far, including children the processes reaped
+    /// (microseconds). Usage is the difference between two samples.
+    pub cpu_time_us: u64,
+    /// Resident memory.
+    pub memory_bytes: u64,
+    /// Number of processes.
+    pub processes: u32,
+    /// GPU memory held by the processes; None without an NVIDIA GPU.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub gpu_memory_bytes: Option<u64>,
+}
+
 // ============================================================================
 // Main Chat Response Types
 // ============================================================================
@@ -1646,6 +2445,24 @@ pub struct WorkspaceChatHistoryRepairResponse {
     pub failed_files: usize,
 }
 
+/// Result of an hstry database integrity check.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HistoryIntegrityResponse {
+    /// Path of the checked database (None if the user has no hstry database).
+    pub database: Option<String>,
+    /// Problems reported by SQLite (empty when healthy).
+    pub problems: Vec<String>,
+    /// Newest snapshot that passes a quick check (looked up only when the
+    /// check failed).
+    pub latest_good_backup: Option<String>,
+}
+
+impl HistoryIntegrityResponse {
+    pub fn is_ok(&self) -> bool {
+        self.problems.is_empty()
+    }
+}
+
 /// Chat message part protocol shape for runner communication.
 ///
 /// This is a compatibility alias to the neutral projection DTO so chat/history
@@ -2222,6 +3039,12 @@ pub struct PiSessionInfo {
     pub provider: Option<String>,
     /// Model (if set).
     pub model: Option<String>,
+    /// Harness the session runs (None = Pi).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub harness: Option<String>,
+    /// Sandbox profile in effect (None = unsandboxed).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sandbox_profile: Option<String>,
 }
 
 /// Pi session lifecycle state.
@@ -2277,6 +3100,16 @@ pub struct PiStateResponse {
 /// The pi_manager translates native Pi events before broadcasting.
 pub type PiEventWrapper = oqto_protocol::events::Event;
 
+/// Response to an interjection.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PiInterjectedResponse {
+    /// The session the interjection was sent to.
+    pub session_id: String,
+    pub interjection_id: String,
+    /// Whether it was steered into the running turn or queued.
+    pub delivery: oqto_protocol::events::InterjectionDelivery,
+}
+
 /// Response confirming Pi subscription started.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct PiSubscribedResponse {
@@ -2445,6 +3278,431 @@ pub struct PiExportHtmlResultResponse {
     pub path: String,
 }
 
+// ============================================================================
+// Tool Approval Response Types
+// ============================================================================
+
+/// A tool call paused until a user approves or denies it.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ToolApproval {
+    pub approval_id: String,
+    pub tool_call_id: String,
+    /// Tool name.
+    pub name: String,
+    /// Tool arguments.
+    pub input: serde_json::Value,
+    /// Approval rule the call matched.
+    pub rule: String,
+    /// Unix ms.
+    pub requested_at: i64,
+    /// Unix ms after which the call is denied.
+    pub expires_at: i64,
+}
+
+/// Tool calls waiting for approval.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ToolApprovalListResponse {
+    pub session_id: String,
+    pub approvals: Vec<ToolApproval>,
+}
+
+// ============================================================================
+// Harness Diagnostics Response Types
+// ============================================================================
+
+/// Summary of a crash bundle.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CrashBundleSummary {
+    /// Bundle ID.
+    pub id: String,
+    /// Runner session ID of the crashed harness.
+    pub session_id: String,
+    /// Pi native session ID, if it was known before the crash.
+    #[serde(default)]
+    pub pi_session_id: Option<String>,
+    /// Harness name (e.g. "pi").
+    pub harness: String,
+    /// When the bundle was collected (RFC 3339).
+    pub collected_at: String,
+    /// Exit code, if the process exited normally.
+    #[serde(default)]
+    pub exit_code: Option<i32>,
+    /// Terminating signal, if the process was killed.
+    #[serde(default)]
+    pub signal: Option<i32>,
+    /// Whether a core dump was found.
+    #[serde(default)]
+    pub has_core_dump: bool,
+    /// Last stderr line, for listing.
+    #[serde(default)]
+    pub last_stderr: Option<String>,
+}
+
+/// Response listing crash bundles.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CrashBundleListResponse {
+    pub bundles: Vec<CrashBundleSummary>,
+}
+
+/// Forensic bundle for a harness process that exited abnormally.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CrashBundle {
+    #[serde(flatten)]
+    pub summary: CrashBundleSummary,
+    /// Last stderr lines, oldest first.
+    pub stderr: Vec<String>,
+    /// Tail of the harness session file.
+    #[serde(default)]
+    pub session_file: Option<CrashBundleFile>,
+    /// Core dump location (and content when small enough).
+    #[serde(default)]
+    pub core_dump: Option<CoreDumpInfo>,
+    /// Process environment with secret values redacted.
+    pub environment: EnvFingerprint,
+}
+
+// ============================================================================
+// Agent Artifacts Response Types
+// ============================================================================
+
+/// Response listing a session's artifacts.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ArtifactListResponse {
+    pub artifacts: Vec<oqto_protocol::events::MediaArtifact>,
+}
+
+/// Artifact content.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ArtifactResponse {
+    /// Session the artifact was captured for.
+    pub session_id: String,
+    pub artifact: oqto_protocol::events::MediaArtifact,
+    /// MIME type of `data_base64` (PNG for thumbnails).
+    pub mime_type: String,
+    pub data_base64: String,
+}
+
+// ============================================================================
+// Background Process Response Types
+// ============================================================================
+
+/// When the runner restarts a background process that exited.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RestartPolicy {
+    #[default]
+    Never,
+    /// Restart after a non-zero exit, with backoff, up to a limit.
+    OnFailure,
+}
+
+/// Lifecycle state of a background process.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BackgroundProcessStatus {
+    Running,
+    /// Crashed; waiting for the restart backoff.
+    Restarting,
+    /// Exited on its own.
+    Exited,
+    /// Stopped by a user, the agent, or session cleanup.
+    Stopped,
+}
+
+/// A background process started for a session.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BackgroundProcessInfo {
+    pub id: String,
+    pub session_id: String,
+    pub name: String,
+    pub command: Vec<String>,
+    pub cwd: PathBuf,
+    pub restart: RestartPolicy,
+    pub status: BackgroundProcessStatus,
+    /// PID of the current (or last) run.
+    #[serde(default)]
+    pub pid: Option<u32>,
+    /// Exit code of the last run.
+    #[serde(default)]
+    pub exit_code: Option<i32>,
+    /// Restarts after crashes so far.
+    pub restarts: u32,
+    /// First start (RFC 3339).
+    pub started_at: String,
+    /// When the process last exited or was stopped (RFC 3339).
+    #[serde(default)]
+    pub ended_at: Option<String>,
+}
+
+/// Response listing background processes.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BackgroundProcessListResponse {
+    pub processes: Vec<BackgroundProcessInfo>,
+}
+
+/// Recent output of a background process (stdout and stderr interleaved).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BackgroundProcessLogsResponse {
+    pub id: String,
+    pub lines: Vec<String>,
+}
+
+/// Output of one scanner.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DependencyScannerRun {
+    pub scanner: DependencyScanner,
+    /// Why the scanner did not run (binary or lockfile missing).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub skipped: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub exit_code: Option<i32>,
+    /// JSON report on stdout.
+    #[serde(default)]
+    pub stdout: String,
+    /// Tail of stderr.
+    #[serde(default)]
+    pub stderr: String,
+    #[serde(default)]
+    pub timed_out: bool,
+    /// Whether stdout was cut at the output limit.
+    #[serde(default)]
+    pub truncated: bool,
+    #[serde(default)]
+    pub duration_ms: u64,
+}
+
+/// Result of a dependency scan.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DependencyScanResponse {
+    pub workspace_path: PathBuf,
+    pub runs: Vec<DependencyScannerRun>,
+}
+
+/// Where a file version comes from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FileVersionSource {
+    /// Runner snapshot taken at the start or end of an agent turn.
+    Snapshot,
+    /// Commit in the workspace's own git repository.
+    Git,
+}
+
+/// One version of a file.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FileVersion {
+    /// `snapshot:<commit>` or `git:<commit>`.
+    pub id: String,
+    pub source: FileVersionSource,
+    /// Commit hash in the snapshot store or workspace repository.
+    pub commit: String,
+    /// When the version was recorded (Unix milliseconds).
+    pub timestamp: i64,
+    /// Snapshot label (e.g. "before turn") or commit subject.
+    pub label: String,
+    /// Session whose turn the snapshot was taken for.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub session_id: Option<String>,
+    /// The file was deleted in this version.
+    #[serde(default)]
+    pub deleted: bool,
+}
+
+/// Versions of a file, newest first.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FileVersionsResponse {
+    pub path: String,
+    pub versions: Vec<FileVersion>,
+}
+
+/// A file's content at a version.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FileVersionContentResponse {
+    pub path: String,
+    pub version: FileVersion,
+    pub content_base64: String,
+    pub size: u64,
+}
+
+/// How a file differs in the index or the working tree.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum GitChangeKind {
+    Added,
+    Modified,
+    Deleted,
+    Renamed,
+    Copied,
+    TypeChanged,
+    Untracked,
+    Conflicted,
+}
+
+/// A changed file in `git status`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct GitStatusEntry {
+    /// Path relative to the repository root.
+    pub path: String,
+    /// Path before a rename or copy.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub orig_path: Option<String>,
+    /// Change staged in the index.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub staged: Option<GitChangeKind>,
+    /// Change in the working tree not staged yet.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub unstaged: Option<GitChangeKind>,
+}
+
+/// Status of a workspace repository.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct GitStatusResponse {
+    /// Current branch; None for a detached HEAD.
+    pub branch: Option<String>,
+    /// Commit of HEAD; None before the first commit.
+    pub head: Option<String>,
+    pub upstream: Option<String>,
+    /// Commits ahead of and behind the upstream.
+    pub ahead: u32,
+    pub behind: u32,
+    pub entries: Vec<GitStatusEntry>,
+}
+
+/// A unified diff.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GitDiffResponse {
+    pub diff: String,
+    /// The diff was cut off at the size limit.
+    pub truncated: bool,
+}
+
+/// One commit.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct GitCommitInfo {
+    pub commit: String,
+    pub author_name: String,
+    pub author_email: String,
+    /// Author date (Unix milliseconds).
+    pub timestamp: i64,
+    pub subject: String,
+}
+
+/// Commits, newest first.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GitLogResponse {
+    pub commits: Vec<GitCommitInfo>,
+}
+
+/// A local branch.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct GitBranch {
+    pub name: String,
+    pub commit: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub upstream: Option<String>,
+    pub current: bool,
+}
+
+/// Local branches.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GitBranchesResponse {
+    pub current: Option<String>,
+    pub branches: Vec<GitBranch>,
+}
+
+/// Result of a push.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GitPushResponse {
+    pub remote: String,
+    pub branch: String,
+    /// Git's progress output.
+    pub output: String,
+}
+
+/// A result column of a data query.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DataQueryColumn {
+    pub name: String,
+    /// DuckDB type name (e.g. `BIGINT`, `VARCHAR`, `TIMESTAMP`).
+    #[serde(rename = "type")]
+    pub data_type: String,
+}
+
+/// Result of a data query.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DataQueryResponse {
+    pub columns: Vec<DataQueryColumn>,
+    /// One JSON array per row, in column order.
+    pub rows: Vec<Vec<Value>>,
+    /// More rows matched than `max_rows`, or the output hit the size limit.
+    pub truncated: bool,
+    pub elapsed_ms: u64,
+}
+
+/// Outcome of a smoke check.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SmokeCheckResponse {
+    /// None when the check was killed by a signal or timed out.
+    pub exit_code: Option<i32>,
+    pub timed_out: bool,
+    /// Tail of stdout and stderr, interleaved.
+    pub output: String,
+    /// Earlier output was dropped.
+    pub output_truncated: bool,
+    pub duration_ms: u64,
+}
+
+/// A file captured into a crash bundle.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CrashBundleFile {
+    pub path: String,
+    pub size_bytes: u64,
+    /// Whether only the tail of the file was captured.
+    pub truncated: bool,
+    pub content: String,
+}
+
+/// Where a core dump for the crashed process can be found.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CoreDumpInfo {
+    /// "file" or "systemd-coredump".
+    pub source: String,
+    #[serde(default)]
+    pub path: Option<String>,
+    #[serde(default)]
+    pub size_bytes: Option<u64>,
+    /// Base64 core file content, only for small core files.
+    #[serde(default)]
+    pub data_base64: Option<String>,
+    /// `coredumpctl info` output (includes a backtrace when available).
+    #[serde(default)]
+    pub details: Option<String>,
+}
+
+/// Runtime fingerprint of the crashed process.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EnvFingerprint {
+    pub runner_version: String,
+    #[serde(default)]
+    pub os_release: Option<String>,
+    pub arch: String,
+    /// Harness binary.
+    pub binary: String,
+    /// Working directory.
+    pub cwd: String,
+    pub sandboxed: bool,
+    #[serde(default)]
+    pub pid: Option<u32>,
+    /// `/proc/sys/kernel/core_pattern` at collection time.
+    #[serde(default)]
+    pub core_pattern: Option<String>,
+    /// Soft `RLIMIT_CORE` in bytes (None = unlimited).
+    #[serde(default)]
+    pub core_limit: Option<u64>,
+    /// Environment variables; values of secret-looking keys are redacted.
+    pub vars: std::collections::BTreeMap<String, String>,
+}
+
 // ============================================================================
 // Error Types
 // ============================================================================
@@ -2458,6 +3716,14 @@ pub struct ErrorResponse {
     pub message: String,
 }
 
+impl std::fmt::Display for ErrorResponse {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "runner error ({:?}): {}", self.code, self.message)
+    }
+}
+
+impl std::error::Error for ErrorResponse {}
+
 /// Error codes.
 #[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
 #[serde(rename_all = "snake_case")]
@@ -2487,6 +3753,10 @@ pub enum ErrorCode {
     NotADirectory,
     /// Not a file.
     NotAFile,
+    /// Workspace is encrypted and must be unlocked first.
+    WorkspaceLocked,
+    /// Wrong passphrase for an encrypted workspace.
+    WrongPassphrase,
 
     // Session errors
     /// Session not found.
@@ -2514,6 +3784,40 @@ pub enum ErrorCode {
     /// Sandbox requested but not available or misconfigured.
     SandboxError,
 
+    // Diagnostics errors
+    /// Crash bundle not found.
+    CrashBundleNotFound,
+    /// Artifact not found.
+    ArtifactNotFound,
+
+    // File history errors
+    /// No such version of the file (or it did not exist in that version).
+    FileVersionNotFound,
+
+    // Git errors
+    /// The workspace is not inside a git repository.
+    NotAGitRepository,
+    /// A git command failed; the message carries git's error output.
+    GitFailed,
+
+    // Data query errors
+    /// `duckdb` is not installed for the runner's user.
+    DataQueryUnavailable,
+    /// The query ran longer than its time limit.
+    DataQueryTimeout,
+    /// DuckDB rejected or failed the query; the message carries its error.
+    DataQueryFailed,
+
+    // Background process errors
+    /// Background process not found.
+    BackgroundProcessNotFound,
+    /// Session already runs the maximum number of background processes.
+    BackgroundProcessLimit,
+
+    // Tool approval errors
+    /// No pending tool approval with this ID (resolved or timed out).
+    ToolApprovalNotFound,
+
     // Generic errors
     /// IO error.
     IoError,
@@ -2608,6 +3912,8 @@ mod tests {
                 session_file: None,
                 continue_session: None,
                 env: HashMap::new(),
+                harness: None,
+                sandbox_profile: Some("net-off".to_string()),
             },
         });
 
@@ -2615,6 +3921,7 @@ mod tests {
         assert!(json.contains("pi_create_session"));
         assert!(json.contains("ses_123"));
         assert!(json.contains("anthropic"));
+        assert!(json.contains("\"sandbox_profile\":\"net-off\""));
 
         let parsed: RunnerRequest = serde_json::from_str(&json).unwrap();
         match parsed {
@@ -2634,6 +3941,7 @@ mod tests {
             session_id: "ses_123".to_string(),
             runner_id: "local".to_string(),
             ts: 1738764000000,
+            seq: None,
             payload: EventPayload::AgentWorking {
                 phase: AgentPhase::Generating,
                 detail: None,
@@ -2658,6 +3966,8 @@ mod tests {
             cwd: PathBuf::from("/home/user/project"),
             provider: Some("anthropic".to_string()),
             model: Some("claude-sonnet-4-20250514".to_string()),
+            harness: None,
+            sandbox_profile: None,
         };
 
         let json = serde_json::to_string(&info).unwrap();
@@ -2713,4 +4023,30 @@ mod tests {
         let proto = agent_msg_to_chat_proto(&msg, 0, "sess-1");
         assert_eq!(proto.client_id.as_deref(), Some("cid-123"));
     }
+
+    #[test]
+    fn workspace_encryption_passphrase_is_redacted() {
+        let req = RunnerRequest::WorkspaceEncryption(WorkspaceEncryptionRequest {
+            workspace_path: PathBuf::from("/home/user/oqto/client-a"),
+            action: WorkspaceEncryptionAction::Unlock {
+                passphrase: Passphrase::new("hunter2-hunter2"),
+            },
+        });
+
+        assert!(!format!("{req:?}").contains("hunter2"));
+
+        let json = serde_json::to_string(&req).unwrap();
+        assert!(json.contains("\"type\":\"workspace_encryption\""));
+        assert!(json.contains("\"action\":\"unlock\""));
+        let parsed: RunnerRequest = serde_json::from_str(&json).unwrap();
+        match parsed {
+            RunnerRequest::WorkspaceEncryption(r) => match r.action {
+                WorkspaceEncryptionAction::Unlock { passphrase } => {
+                    assert_eq!(passphrase.expose(), "hunter2-hunter2");
+                }
+                other => panic!("unexpected action: {other:?}"),
+            },
+            _ => panic!("wrong variant"),
+        }
+    }
 }
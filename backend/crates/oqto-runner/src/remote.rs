@@ -0,0 +1,61 @@
+//! TCP transport for remote runner hosts.
+//!
+//! A runner normally only listens on its Unix socket. With `--listen` it also
+//! accepts TCP connections so a backend on another machine can schedule
+//! sessions on it. TCP clients authenticate with a shared token: the first
+//! line of every connection must be `AUTH <token>`, after which the
+//! connection speaks the same newline-delimited JSON protocol as the socket.
+//!
+//! The token and all traffic travel in cleartext, so remote runners belong on
+//! a private network or behind a tunnel.
+
+/// Prefix of the authentication line sent first on TCP connections.
+pub const AUTH_PREFIX: &str = "AUTH ";
+
+/// Environment variable holding the token when `--token-file` is not given.
+pub const TOKEN_ENV: &str = "OQTO_RUNNER_TOKEN";
+
+/// The authentication line a client sends for `token`.
+pub fn auth_line(token: &str) -> String {
+    format!("{AUTH_PREFIX}{token}\n")
+}
+
+/// Whether `line` (as read, including its newline) authenticates with
+/// `token`.
+pub fn check_auth_line(line: &str, token: &str) -> bool {
+    line.trim_end_matches(['\r', '\n'])
+        .strip_prefix(AUTH_PREFIX)
+        .is_some_and(|given| token_matches(given, token))
+}
+
+/// Whether `given` is `token`, in the same time for every wrong token of a
+/// given length. An empty token matches nothing.
+pub fn token_matches(given: &str, token: &str) -> bool {
+    !token.is_empty()
+        && given.len() == token.len()
+        && given
+            .bytes()
+            .zip(token.bytes())
+            .fold(0u8, |acc, (a, b)| acc | (a ^ b))
+            == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn accepts_only_the_configured_token() {
+        let line = auth_line("s3cret");
+        assert_eq!(line, "AUTH s3cret\n");
+        assert!(check_auth_line(&line, "s3cret"));
+        assert!(check_auth_line("AUTH s3cret\r\n", "s3cret"));
+
+        assert!(!check_auth_line(&line, "s3cres"));
+        assert!(!check_auth_line(&line, "s3cret2"));
+        assert!(!check_auth_line("s3cret\n", "s3cret"));
+        assert!(!check_auth_line("{\"type\":\"ping\"}\n", "s3cret"));
+        // An empty token never authenticates.
+        assert!(!check_auth_line("AUTH \n", ""));
+    }
+}
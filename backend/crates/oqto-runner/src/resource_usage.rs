@@ -0,0 +1,219 @@
+//! Resource usage of a session's processes, read from procfs.
+//!
+//! A session is the process trees under the fileserver, terminal, background
+//! processes and agents the runner started in its workspace. CPU time counts
+//! children the processes already reaped, so the compiler runs of a build
+//! show up even though each lives only briefly. GPU memory comes from
+//! `nvidia-smi` when it is installed.
+
+use std::collections::{HashMap, HashSet};
+use std::process::Stdio;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+
+use log::debug;
+
+/// Time limit for one `nvidia-smi` query.
+const GPU_QUERY_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Set once `nvidia-smi` turned out to be missing; it is not looked for again.
+static NO_NVIDIA_SMI: AtomicBool = AtomicBool::new(false);
+
+/// Usage summed over process trees.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TreeUsage {
+    /// CPU time including reaped children (microseconds).
+    pub cpu_time_us: u64,
+    /// Resident memory.
+    pub memory_bytes: u64,
+    /// Every process in the trees.
+    pub pids: Vec<u32>,
+}
+
+/// The fields of `/proc/<pid>/stat` that matter here.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct ProcStat {
+    pid: u32,
+    ppid: u32,
+    /// utime + stime + cutime + cstime, in clock ticks.
+    cpu_ticks: u64,
+    rss_pages: u64,
+}
+
+/// Usage of the process trees rooted at `roots`. Blocks on procfs reads.
+pub fn tree_usage(roots: &[u32]) -> TreeUsage {
+    let Ok(entries) = std::fs::read_dir("/proc") else {
+        return TreeUsage::default();
+    };
+    let stats: Vec<ProcStat> = entries
+        .flatten()
+        .filter(|entry| {
+            entry
+                .file_name()
+                .to_str()
+                .is_some_and(|name| name.bytes().all(|b| b.is_ascii_digit()))
+        })
+        // Processes exit between listing and reading.
+        .filter_map(|entry| std::fs::read_to_string(entry.path().join("stat")).ok())
+        .filter_map(|contents| parse_stat(&contents))
+        .collect();
+    // SAFETY: sysconf has no preconditions.
+    let (ticks_per_sec, page_size) = unsafe {
+        (
+            libc::sysconf(libc::_SC_CLK_TCK),
+            libc::sysconf(libc::_SC_PAGESIZE),
+        )
+    };
+    sum_trees(
+        &stats,
+        roots,
+        1_000_000 / ticks_per_sec.max(1) as u64,
+        page_size.max(1) as u64,
+    )
+}
+
+/// Parse `/proc/<pid>/stat`. The command name may contain spaces and
+/// parentheses, so fields are counted from its closing parenthesis.
+fn parse_stat(contents: &str) -> Option<ProcStat> {
+    let (pid, rest) = contents.split_once(" (")?;
+    let (_, rest) = rest.rsplit_once(") ")?;
+    let fields: Vec<&str> = rest.split_whitespace().collect();
+    // Field 3 of the man page (state) is fields[0].
+    let field = |n: usize| fields.get(n - 3).and_then(|f| f.parse::<u64>().ok());
+    Some(ProcStat {
+        pid: pid.trim().parse().ok()?,
+        ppid: field(4)? as u32,
+        cpu_ticks: field(14)? + field(15)? + field(16)? + field(17)?,
+        rss_pages: field(24)?,
+    })
+}
+
+fn sum_trees(stats: &[ProcStat], roots: &[u32], tick_us: u64, page_size: u64) -> TreeUsage {
+    let mut children: HashMap<u32, Vec<&ProcStat>> = HashMap::new();
+    let mut by_pid = HashMap::new();
+    for stat in stats {
+        children.entry(stat.ppid).or_default().push(stat);
+        by_pid.insert(stat.pid, stat);
+    }
+
+    let mut usage = TreeUsage::default();
+    let mut seen = HashSet::new();
+    let mut queue: Vec<&ProcStat> = roots
+        .iter()
+        .filter_map(|pid| by_pid.get(pid))
+        .copied()
+        .collect();
+    while let Some(stat) = queue.pop() {
+        if !seen.insert(stat.pid) {
+            continue;
+        }
+        usage.cpu_time_us += stat.cpu_ticks * tick_us;
+        usage.memory_bytes += stat.rss_pages * page_size;
+        usage.pids.push(stat.pid);
+        if let Some(kids) = children.get(&stat.pid) {
+            queue.extend(kids);
+        }
+    }
+    usage.pids.sort_unstable();
+    usage
+}
+
+/// GPU memory held by `pids`. None when no NVIDIA GPU can be queried.
+pub async fn gpu_memory(pids: &[u32]) -> Option<u64> {
+    if NO_NVIDIA_SMI.load(Ordering::Relaxed) {
+        return None;
+    }
+    let child = tokio::process::Command::new("nvidia-smi")
+        .args([
+            "--query-compute-apps=pid,used_memory",
+            "--format=csv,noheader,nounits",
+        ])
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .kill_on_drop(true)
+        .spawn();
+    let child = match child {
+        Ok(child) => child,
+        Err(e) => {
+            if e.kind() == std::io::ErrorKind::NotFound {
+                NO_NVIDIA_SMI.store(true, Ordering::Relaxed);
+            }
+            return None;
+        }
+    };
+    let output = match tokio::time::timeout(GPU_QUERY_TIMEOUT, child.wait_with_output()).await {
+        Ok(Ok(output)) if output.status.success() => output,
+        Ok(_) => return None,
+        Err(_) => {
+            debug!("nvidia-smi did not answer within {:?}", GPU_QUERY_TIMEOUT);
+            return None;
+        }
+    };
+    let pids: HashSet<u32> = pids.iter().copied().collect();
+    Some(parse_gpu_apps(
+        &String::from_utf8_lossy(&output.stdout),
+        &pids,
+    ))
+}
+
+/// Sum the memory of `pids` in `nvidia-smi --query-compute-apps` CSV
+/// (MiB per process).
+fn parse_gpu_apps(csv: &str, pids: &HashSet<u32>) -> u64 {
+    csv.lines()
+        .filter_map(|line| {
+            let (pid, memory) = line.split_once(',')?;
+            let pid: u32 = pid.trim().parse().ok()?;
+            let mib: u64 = memory.trim().parse().ok()?;
+            pids.contains(&pid).then_some(mib * 1024 * 1024)
+        })
+        .sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_proc_stat() {
+        let stat = "4242 (cargo (build)) R 4200 4242 4200 0 -1 4194304 900 0 0 0 150 25 300 40 20 0 8 0 123456 987654321 2048 18446744073709551615 0 0 0 0 0 0 0 0 0 0 0 0 17 3 0 0 0 0 0\n";
+        assert_eq!(
+            parse_stat(stat),
+            Some(ProcStat {
+                pid: 4242,
+                ppid: 4200,
+                cpu_ticks: 515,
+                rss_pages: 2048,
+            })
+        );
+        assert_eq!(parse_stat("garbage"), None);
+    }
+
+    #[test]
+    fn sums_process_trees() {
+        let stat = |pid, ppid, cpu_ticks, rss_pages| ProcStat {
+            pid,
+            ppid,
+            cpu_ticks,
+            rss_pages,
+        };
+        let stats = [
+            stat(1, 0, 1000, 100),
+            stat(10, 1, 10, 1),
+            stat(11, 10, 20, 2),
+            stat(12, 11, 30, 3),
+            stat(20, 1, 40, 4),
+        ];
+        let usage = sum_trees(&stats, &[10, 11, 99], 10_000, 4096);
+        assert_eq!(usage.pids, vec![10, 11, 12]);
+        assert_eq!(usage.cpu_time_us, 600_000);
+        assert_eq!(usage.memory_bytes, 6 * 4096);
+    }
+
+    #[test]
+    fn sums_gpu_memory_of_session_processes() {
+        let csv = "12, 512\n13, 1024\n77, 2048\n[Not Supported]\n";
+        let pids = HashSet::from([12, 13]);
+        assert_eq!(parse_gpu_apps(csv, &pids), 1536 * 1024 * 1024);
+    }
+}
@@ -0,0 +1,138 @@
+//! Secrets injected into sessions.
+//!
+//! oqto decrypts the user's secrets and passes them with `StartSession`.
+//! Secret files are written to a per-session directory under
+//! `$XDG_RUNTIME_DIR` (owner only, usually a tmpfs) instead of the
+//! workspace, so they never end up in commits, exports or backups. Processes
+//! started in the workspace get the secret variables and `OQTO_SECRETS_DIR`.
+
+use std::collections::HashMap;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use crate::protocol::SessionSecrets;
+
+/// Variable pointing at the directory holding a session's secret files.
+pub const SECRETS_DIR_ENV: &str = "OQTO_SECRETS_DIR";
+
+/// Directory holding the session's secret files.
+pub fn secrets_dir(session_id: &str) -> PathBuf {
+    let runtime_dir = std::env::var("XDG_RUNTIME_DIR").unwrap_or_else(|_| "/tmp".to_string());
+    PathBuf::from(runtime_dir)
+        .join("oqto-secrets")
+        .join(session_id)
+}
+
+/// Write the session's secret files, readable by the owner only, and return
+/// their directory. None when the session has no secret files.
+pub fn write_files(session_id: &str, secrets: &SessionSecrets) -> std::io::Result<Option<PathBuf>> {
+    if secrets.files.is_empty() {
+        return Ok(None);
+    }
+    let dir = secrets_dir(session_id);
+    // Files of secrets deleted since the last start must not linger.
+    let _ = std::fs::remove_dir_all(&dir);
+    create_private_dir(&dir)?;
+    for (name, content) in &secrets.files {
+        if !is_file_name(name) {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                format!("invalid secret file name {name:?}"),
+            ));
+        }
+        let mut options = std::fs::OpenOptions::new();
+        options.write(true).create_new(true);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::OpenOptionsExt;
+            options.mode(0o600);
+        }
+        options
+            .open(dir.join(name))?
+            .write_all(content.as_bytes())?;
+    }
+    Ok(Some(dir))
+}
+
+/// Remove the session's secret files.
+pub fn remove_files(session_id: &str) {
+    let _ = std::fs::remove_dir_all(secrets_dir(session_id));
+}
+
+/// Environment variables processes of the session get, given the directory
+/// its files were written to.
+pub fn process_env(secrets: &SessionSecrets, files_dir: Option<&Path>) -> HashMap<String, String> {
+    let mut env: HashMap<String, String> = secrets
+        .env
+        .iter()
+        .map(|(key, value)| (key.clone(), value.clone()))
+        .collect();
+    if let Some(dir) = files_dir {
+        env.insert(SECRETS_DIR_ENV.to_string(), dir.display().to_string());
+    }
+    env
+}
+
+fn create_private_dir(dir: &Path) -> std::io::Result<()> {
+    std::fs::create_dir_all(dir)?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        if let Some(parent) = dir.parent() {
+            std::fs::set_permissions(parent, std::fs::Permissions::from_mode(0o700))?;
+        }
+        std::fs::set_permissions(dir, std::fs::Permissions::from_mode(0o700))?;
+    }
+    Ok(())
+}
+
+/// A plain file name: no separators, not `.` or `..`.
+fn is_file_name(name: &str) -> bool {
+    !name.is_empty() && name != "." && name != ".." && !name.contains(['/', '\\', '\0'])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_write_and_remove_files() {
+        let session_id = format!("test-secrets-{}", std::process::id());
+        let secrets = SessionSecrets {
+            env: [("API_TOKEN".to_string(), "t0ken".to_string())].into(),
+            files: [("service-account.json".to_string(), "{}".to_string())].into(),
+        };
+        let dir = write_files(&session_id, &secrets).unwrap().unwrap();
+        let file = dir.join("service-account.json");
+        assert_eq!(std::fs::read_to_string(&file).unwrap(), "{}");
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = std::fs::metadata(&file).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o600);
+        }
+
+        let env = process_env(&secrets, Some(&dir));
+        assert_eq!(env["API_TOKEN"], "t0ken");
+        assert_eq!(env[SECRETS_DIR_ENV], dir.display().to_string());
+
+        remove_files(&session_id);
+        assert!(!dir.exists());
+    }
+
+    #[test]
+    fn test_rejects_paths() {
+        let secrets = SessionSecrets {
+            files: [("../escape".to_string(), "x".to_string())].into(),
+            ..Default::default()
+        };
+        let session_id = format!("test-secrets-bad-{}", std::process::id());
+        assert!(write_files(&session_id, &secrets).is_err());
+        remove_files(&session_id);
+        assert!(
+            write_files("unused", &SessionSecrets::default())
+                .unwrap()
+                .is_none()
+        );
+    }
+}
@@ -0,0 +1,171 @@
+//! Workspace shell environments.
+//!
+//! oqto resolves the `[shell]` section of a workspace's `.oqto/config.toml`
+//! and passes it with `StartSession`. The terminal gets it through a
+//! `ZDOTDIR` whose startup files chain to the user's own and then source the
+//! workspace profile, so the workspace wins over the user's rc files. Agents
+//! started in the workspace get its variables and `PATH` additions.
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use crate::protocol::ShellEnvironment;
+
+/// Environment variables a process started in the workspace gets. `base_path`
+/// is the `PATH` the additions are prepended to.
+pub fn process_env(shell: &ShellEnvironment, base_path: Option<&str>) -> HashMap<String, String> {
+    let mut env: HashMap<String, String> = shell
+        .env
+        .iter()
+        .map(|(key, value)| (key.clone(), value.clone()))
+        .collect();
+    if !shell.path.is_empty() {
+        let mut path: Vec<String> = shell
+            .path
+            .iter()
+            .map(|dir| dir.display().to_string())
+            .collect();
+        path.extend(base_path.filter(|p| !p.is_empty()).map(str::to_string));
+        env.insert("PATH".to_string(), path.join(":"));
+    }
+    env
+}
+
+/// POSIX shell script applying the environment in an interactive shell.
+pub fn profile(shell: &ShellEnvironment) -> String {
+    let mut script = String::from("# Generated by oqto from the workspace's .oqto/config.toml.\n");
+    if !shell.path.is_empty() {
+        let dirs: Vec<String> = shell
+            .path
+            .iter()
+            .map(|dir| quote(&dir.display().to_string()))
+            .collect();
+        script.push_str(&format!("export PATH={}:\"$PATH\"\n", dirs.join(":")));
+    }
+    for (key, value) in &shell.env {
+        script.push_str(&format!("export {key}={}\n", quote(value)));
+    }
+    for (name, command) in &shell.aliases {
+        script.push_str(&format!("alias {}\n", quote(&format!("{name}={command}"))));
+    }
+    script
+}
+
+/// Directory holding the session's zsh startup files.
+pub fn zdotdir(session_id: &str) -> PathBuf {
+    let runtime_dir = std::env::var("XDG_RUNTIME_DIR").unwrap_or_else(|_| "/tmp".to_string());
+    PathBuf::from(runtime_dir)
+        .join("oqto-shell")
+        .join(session_id)
+}
+
+/// Write the session's zsh startup files and return the directory to use as
+/// `ZDOTDIR`. `user_zdotdir` is where the user's own startup files live.
+pub fn write_zdotdir(
+    session_id: &str,
+    shell: &ShellEnvironment,
+    user_zdotdir: &Path,
+) -> std::io::Result<PathBuf> {
+    let dir = zdotdir(session_id);
+    std::fs::create_dir_all(&dir)?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        std::fs::set_permissions(&dir, std::fs::Permissions::from_mode(0o700))?;
+    }
+
+    let own = quote(&dir.display().to_string());
+    let user = quote(&user_zdotdir.display().to_string());
+    let profile_path = dir.join("profile.sh");
+    std::fs::write(&profile_path, profile(shell))?;
+
+    // ZDOTDIR points at the user's directory while their files run, and back
+    // here until .zshrc has sourced the profile. .zlogin runs last and leaves
+    // it at the user's.
+    for file in [".zshenv", ".zprofile"] {
+        std::fs::write(dir.join(file), chain(file, &user, Some(&own), None))?;
+    }
+    let source_profile = format!(". {}", quote(&profile_path.display().to_string()));
+    std::fs::write(
+        dir.join(".zshrc"),
+        chain(".zshrc", &user, Some(&own), Some(&source_profile)),
+    )?;
+    std::fs::write(dir.join(".zlogin"), chain(".zlogin", &user, None, None))?;
+    Ok(dir)
+}
+
+/// Remove the session's zsh startup files, if any.
+pub fn remove_zdotdir(session_id: &str) {
+    let _ = std::fs::remove_dir_all(zdotdir(session_id));
+}
+
+fn chain(file: &str, user: &str, restore: Option<&str>, then: Option<&str>) -> String {
+    let mut script =
+        format!("ZDOTDIR={user}\n[[ -f \"$ZDOTDIR/{file}\" ]] && . \"$ZDOTDIR/{file}\"\n");
+    if let Some(then) = then {
+        script.push_str(then);
+        script.push('\n');
+    }
+    if let Some(own) = restore {
+        script.push_str(&format!("ZDOTDIR={own}\n"));
+    }
+    script
+}
+
+/// Quote `value` as a single shell word.
+fn quote(value: &str) -> String {
+    format!("'{}'", value.replace('\'', r"'\''"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn shell() -> ShellEnvironment {
+        ShellEnvironment {
+            path: vec![
+                PathBuf::from("/work/node_modules/.bin"),
+                PathBuf::from("/opt/go/bin"),
+            ],
+            env: [("GREETING".to_string(), "it's ok".to_string())].into(),
+            aliases: [("t".to_string(), "cargo test".to_string())].into(),
+        }
+    }
+
+    #[test]
+    fn test_process_env() {
+        let env = process_env(&shell(), Some("/usr/bin"));
+        assert_eq!(env["PATH"], "/work/node_modules/.bin:/opt/go/bin:/usr/bin");
+        assert_eq!(env["GREETING"], "it's ok");
+
+        let env = process_env(&ShellEnvironment::default(), Some("/usr/bin"));
+        assert!(env.is_empty());
+    }
+
+    #[test]
+    fn test_profile() {
+        assert_eq!(
+            profile(&shell()),
+            "# Generated by oqto from the workspace's .oqto/config.toml.\n\
+             export PATH='/work/node_modules/.bin':'/opt/go/bin':\"$PATH\"\n\
+             export GREETING='it'\\''s ok'\n\
+             alias 't=cargo test'\n"
+        );
+    }
+
+    #[test]
+    fn test_write_zdotdir() {
+        let session_id = format!("test-{}", std::process::id());
+        let dir = write_zdotdir(&session_id, &shell(), Path::new("/home/alice")).unwrap();
+
+        let zshrc = std::fs::read_to_string(dir.join(".zshrc")).unwrap();
+        assert!(zshrc.starts_with("ZDOTDIR='/home/alice'\n"));
+        assert!(zshrc.contains("profile.sh'\n"));
+        assert!(zshrc.ends_with(&format!("ZDOTDIR='{}'\n", dir.display())));
+        let zlogin = std::fs::read_to_string(dir.join(".zlogin")).unwrap();
+        assert!(!zlogin.contains(&dir.display().to_string()));
+
+        remove_zdotdir(&session_id);
+        assert!(!dir.exists());
+    }
+}
@@ -0,0 +1,170 @@
+//! Smoke checks of workspace templates.
+//!
+//! To test a template, the backend creates a throwaway project from it and
+//! has the runner run the template's declared check command there, as the
+//! workspace's user, through `sh -c`. Standard error is merged into
+//! standard output and only the tail is kept, which is where build and test
+//! tools put their verdict. A check that outlives its time limit is killed
+//! together with everything it started.
+
+use std::path::Path;
+use std::process::Stdio;
+use std::time::{Duration, Instant};
+
+use log::{info, warn};
+use tokio::io::AsyncReadExt;
+
+use crate::protocol::SmokeCheckResponse;
+
+/// Time limit when the template sets none.
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(300);
+
+/// Upper bound for a requested time limit.
+const MAX_TIMEOUT: Duration = Duration::from_secs(1800);
+
+/// Tail of the output kept.
+const MAX_OUTPUT_BYTES: usize = 64 * 1024;
+
+/// Clamp a requested time limit.
+pub fn effective_timeout(timeout_secs: Option<u64>) -> Duration {
+    timeout_secs
+        .map(Duration::from_secs)
+        .unwrap_or(DEFAULT_TIMEOUT)
+        .clamp(Duration::from_secs(1), MAX_TIMEOUT)
+}
+
+/// Read `reader` to the end, keeping its last `limit` bytes in `out`.
+/// Returns whether anything was dropped.
+async fn read_tail(
+    reader: &mut (impl tokio::io::AsyncRead + Unpin),
+    out: &mut Vec<u8>,
+    limit: usize,
+) -> bool {
+    let mut dropped = false;
+    let mut buf = [0u8; 8192];
+    loop {
+        match reader.read(&mut buf).await {
+            Ok(0) | Err(_) => break,
+            Ok(n) => {
+                out.extend_from_slice(&buf[..n]);
+                // Trim in batches rather than on every read.
+                if out.len() > 2 * limit {
+                    out.drain(..out.len() - limit);
+                    dropped = true;
+                }
+            }
+        }
+    }
+    if out.len() > limit {
+        out.drain(..out.len() - limit);
+        dropped = true;
+    }
+    dropped
+}
+
+/// Run `command` in `dir`. Errors only when the shell cannot be started; a
+/// failing or timed-out check is a result.
+pub async fn run(
+    dir: &Path,
+    command: &str,
+    timeout: Duration,
+) -> std::io::Result<SmokeCheckResponse> {
+    let started = Instant::now();
+    let mut child = tokio::process::Command::new("sh")
+        .arg("-c")
+        .arg(format!("exec 2>&1\n{command}"))
+        .current_dir(dir)
+        .env("CI", "1")
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        // Its own process group, so a timeout can kill what it started.
+        .process_group(0)
+        .kill_on_drop(true)
+        .spawn()?;
+    let pid = child.id();
+
+    let mut stdout = child.stdout.take().expect("stdout is piped");
+    let mut output = Vec::new();
+    let collect = async {
+        let (truncated, status) = tokio::join!(
+            read_tail(&mut stdout, &mut output, MAX_OUTPUT_BYTES),
+            child.wait()
+        );
+        (truncated, status)
+    };
+    let (output_truncated, exit_code, timed_out) =
+        match tokio::time::timeout(timeout, collect).await {
+            Ok((truncated, status)) => (truncated, status.ok().and_then(|s| s.code()), false),
+            Err(_) => {
+                warn!(
+                    "Smoke check timed out after {}s in {}",
+                    timeout.as_secs(),
+                    dir.display()
+                );
+                if let Some(pid) = pid {
+                    // SAFETY: signals the group the child leads; no memory is touched.
+                    unsafe {
+                        libc::killpg(pid as libc::pid_t, libc::SIGKILL);
+                    }
+                }
+                (false, None, true)
+            }
+        };
+
+    let duration_ms = started.elapsed().as_millis() as u64;
+    info!(
+        "Smoke check in {} finished in {} ms (exit {:?}, timed out: {})",
+        dir.display(),
+        duration_ms,
+        exit_code,
+        timed_out
+    );
+    Ok(SmokeCheckResponse {
+        exit_code,
+        timed_out,
+        output: String::from_utf8_lossy(&output).into_owned(),
+        output_truncated,
+        duration_ms,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn output_and_exit_code_are_captured() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("marker"), "ok").unwrap();
+        let result = run(
+            dir.path(),
+            "cat marker; echo oops >&2; exit 3",
+            Duration::from_secs(10),
+        )
+        .await
+        .unwrap();
+        assert_eq!(result.exit_code, Some(3));
+        assert!(!result.timed_out);
+        assert_eq!(result.output, "okoops\n");
+    }
+
+    #[tokio::test]
+    async fn slow_checks_time_out() {
+        let dir = tempfile::tempdir().unwrap();
+        let result = run(dir.path(), "sleep 30", Duration::from_secs(1))
+            .await
+            .unwrap();
+        assert!(result.timed_out);
+        assert_eq!(result.exit_code, None);
+    }
+
+    #[tokio::test]
+    async fn only_the_tail_is_kept() {
+        let mut reader: &[u8] = &[b'x'; 3 * MAX_OUTPUT_BYTES + 7];
+        let mut out = Vec::new();
+        assert!(read_tail(&mut reader, &mut out, MAX_OUTPUT_BYTES).await);
+        assert_eq!(out.len(), MAX_OUTPUT_BYTES);
+        assert_eq!(effective_timeout(Some(99_999)), MAX_TIMEOUT);
+    }
+}
@@ -0,0 +1,353 @@
+//! Human approval of agent tool calls.
+//!
+//! Pi runs tools without asking (`--approve`), so when approvals are enabled
+//! the runner loads a small bridge extension into the session (see
+//! `extensions/oqto-approvals.ts`). Before every tool call the extension asks
+//! the runner through an `input` extension UI request titled
+//! [`APPROVAL_REQUEST_TITLE`]. Calls matching no rule are answered right
+//! away; matching calls emit `tool.approval_required` and wait until the
+//! user approves or denies them (`POST /api/sessions/{id}/approvals/{id}`),
+//! or are denied once `timeout_secs` pass.
+//!
+//! ```toml
+//! [runner.tool_approvals]
+//! enabled = true
+//! timeout_secs = 600
+//!
+//! [[runner.tool_approvals.rules]]
+//! name = "git-push"
+//! tools = ["bash"]
+//! patterns = ['\bgit\s+push\b']
+//! ```
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+use log::warn;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use tokio::sync::Mutex;
+
+use crate::protocol::ToolApproval;
+
+/// Title of the extension UI request the bridge extension sends per call.
+pub const APPROVAL_REQUEST_TITLE: &str = "oqto_approval";
+
+/// The bridge extension loaded into Pi sessions when approvals are enabled.
+const BRIDGE_EXTENSION: &str = include_str!("../extensions/oqto-approvals.ts");
+
+/// Tool approval configuration (`[runner.tool_approvals]`).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ToolApprovalConfig {
+    /// Off by default: a matching call stalls the agent until someone answers.
+    pub enabled: bool,
+    /// Pending calls are denied after this long.
+    pub timeout_secs: u64,
+    /// A call needs approval when it matches any rule.
+    pub rules: Vec<ToolApprovalRule>,
+}
+
+impl Default for ToolApprovalConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            timeout_secs: 600,
+            rules: vec![
+                ToolApprovalRule {
+                    name: "delete".to_string(),
+                    tools: vec!["bash".to_string()],
+                    patterns: vec![r"\brm\s".to_string(), r"\bgit\s+clean\b".to_string()],
+                },
+                ToolApprovalRule {
+                    name: "git-push".to_string(),
+                    tools: vec!["bash".to_string()],
+                    patterns: vec![r"\bgit\s+push\b".to_string()],
+                },
+                ToolApprovalRule {
+                    name: "network".to_string(),
+                    tools: vec![
+                        "fetch".to_string(),
+                        "web_fetch".to_string(),
+                        "web_search".to_string(),
+                    ],
+                    patterns: Vec::new(),
+                },
+            ],
+        }
+    }
+}
+
+/// Calls that need approval.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ToolApprovalRule {
+    /// Reported in `tool.approval_required` events.
+    pub name: String,
+    /// Tool names covered by this rule (case-insensitive).
+    pub tools: Vec<String>,
+    /// Regexes matched against the call: the `command` argument for shell
+    /// tools, the JSON arguments otherwise. Empty matches every call.
+    #[serde(default)]
+    pub patterns: Vec<String>,
+}
+
+#[derive(Debug)]
+struct CompiledRule {
+    name: String,
+    tools: Vec<String>,
+    patterns: Vec<Regex>,
+}
+
+/// Compiled approval rules.
+#[derive(Debug)]
+pub struct ToolApprovalPolicy {
+    rules: Vec<CompiledRule>,
+    timeout: Duration,
+}
+
+impl ToolApprovalPolicy {
+    /// None when approvals are disabled or no rules are configured. Rules
+    /// with an invalid pattern are skipped with a warning.
+    pub fn new(config: &ToolApprovalConfig) -> Option<Self> {
+        if !config.enabled {
+            return None;
+        }
+        let rules: Vec<CompiledRule> = config
+            .rules
+            .iter()
+            .filter_map(|rule| {
+                let patterns = rule
+                    .patterns
+                    .iter()
+                    .map(|p| Regex::new(p))
+                    .collect::<Result<Vec<_>, _>>();
+                match patterns {
+                    Ok(patterns) => Some(CompiledRule {
+                        name: rule.name.clone(),
+                        tools: rule.tools.clone(),
+                        patterns,
+                    }),
+                    Err(e) => {
+                        warn!("Skipping tool approval rule '{}': {}", rule.name, e);
+                        None
+                    }
+                }
+            })
+            .collect();
+        if rules.is_empty() {
+            return None;
+        }
+        Some(Self {
+            rules,
+            timeout: Duration::from_secs(config.timeout_secs.max(1)),
+        })
+    }
+
+    /// How long a call waits for a decision.
+    pub fn timeout(&self) -> Duration {
+        self.timeout
+    }
+
+    /// Name of the first rule `tool_name` called with `input` matches.
+    pub fn check(&self, tool_name: &str, input: &serde_json::Value) -> Option<&str> {
+        let text = call_text(input);
+        self.rules
+            .iter()
+            .find(|rule| {
+                rule.tools.iter().any(|t| t.eq_ignore_ascii_case(tool_name))
+                    && (rule.patterns.is_empty() || rule.patterns.iter().any(|p| p.is_match(&text)))
+            })
+            .map(|rule| rule.name.as_str())
+    }
+}
+
+/// Text patterns are matched against.
+fn call_text(input: &serde_json::Value) -> String {
+    match input.get("command").and_then(|c| c.as_str()) {
+        Some(command) => command.to_string(),
+        None => input.to_string(),
+    }
+}
+
+/// A tool call the bridge extension asks about.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ApprovalCall {
+    pub tool_call_id: String,
+    pub tool_name: String,
+    #[serde(default)]
+    pub input: serde_json::Value,
+}
+
+impl ApprovalCall {
+    /// Parse the bridge extension's request (the `placeholder` of its
+    /// `input` UI request).
+    pub fn parse(method: &str, title: Option<&str>, placeholder: Option<&str>) -> Option<Self> {
+        if method != "input" || title != Some(APPROVAL_REQUEST_TITLE) {
+            return None;
+        }
+        serde_json::from_str(placeholder?).ok()
+    }
+}
+
+/// Answer to the bridge extension, sent as the UI response value.
+pub fn decision_value(approved: bool, reason: Option<&str>) -> String {
+    serde_json::json!({ "approved": approved, "reason": reason }).to_string()
+}
+
+/// Tool calls of one session waiting for a decision, keyed by approval ID
+/// (the ID of the extension UI request).
+#[derive(Debug, Default)]
+pub struct ApprovalGate {
+    pending: Mutex<HashMap<String, ToolApproval>>,
+}
+
+impl ApprovalGate {
+    pub async fn open(&self, approval: ToolApproval) {
+        self.pending
+            .lock()
+            .await
+            .insert(approval.approval_id.clone(), approval);
+    }
+
+    /// Remove a pending approval to resolve it. None when it was already
+    /// resolved or timed out.
+    pub async fn take(&self, approval_id: &str) -> Option<ToolApproval> {
+        self.pending.lock().await.remove(approval_id)
+    }
+
+    /// Pending approvals, oldest first.
+    pub async fn list(&self) -> Vec<ToolApproval> {
+        let mut approvals: Vec<_> = self.pending.lock().await.values().cloned().collect();
+        approvals.sort_by_key(|a| a.requested_at);
+        approvals
+    }
+}
+
+/// Write the bridge extension into the workspace's `.oqto` directory (which
+/// sandboxed sessions can read) and return its path.
+pub fn install_bridge_extension(cwd: &Path) -> Result<PathBuf> {
+    let dir = cwd.join(".oqto").join("extensions");
+    std::fs::create_dir_all(&dir).with_context(|| format!("creating {}", dir.display()))?;
+    let path = dir.join("oqto-approvals.ts");
+    if std::fs::read_to_string(&path).ok().as_deref() != Some(BRIDGE_EXTENSION) {
+        std::fs::write(&path, BRIDGE_EXTENSION)
+            .with_context(|| format!("writing {}", path.display()))?;
+    }
+    Ok(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn policy() -> ToolApprovalPolicy {
+        ToolApprovalPolicy::new(&ToolApprovalConfig {
+            enabled: true,
+            ..Default::default()
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn disabled_by_default() {
+        assert!(ToolApprovalPolicy::new(&ToolApprovalConfig::default()).is_none());
+    }
+
+    #[test]
+    fn matches_shell_commands_by_pattern() {
+        let policy = policy();
+        let bash = |command: &str| serde_json::json!({ "command": command });
+        assert_eq!(policy.check("bash", &bash("rm -rf build")), Some("delete"));
+        assert_eq!(
+            policy.check("bash", &bash("cd repo && git push origin main")),
+            Some("git-push")
+        );
+        assert_eq!(policy.check("bash", &bash("cargo build")), None);
+        assert_eq!(policy.check("bash", &bash("git pushd")), None);
+        // Patterns only apply to the tools of their rule.
+        assert_eq!(policy.check("read", &bash("rm -rf build")), None);
+    }
+
+    #[test]
+    fn rule_without_patterns_matches_every_call() {
+        let policy = policy();
+        let input = serde_json::json!({ "url": "https://example.com" });
+        assert_eq!(policy.check("WEB_FETCH", &input), Some("network"));
+    }
+
+    #[test]
+    fn invalid_patterns_skip_the_rule() {
+        let policy = ToolApprovalPolicy::new(&ToolApprovalConfig {
+            enabled: true,
+            timeout_secs: 60,
+            rules: vec![
+                ToolApprovalRule {
+                    name: "broken".to_string(),
+                    tools: vec!["bash".to_string()],
+                    patterns: vec!["(".to_string()],
+                },
+                ToolApprovalRule {
+                    name: "write".to_string(),
+                    tools: vec!["write".to_string()],
+                    patterns: Vec::new(),
+                },
+            ],
+        })
+        .unwrap();
+        assert_eq!(
+            policy.check("bash", &serde_json::json!({"command": "("})),
+            None
+        );
+        assert_eq!(
+            policy.check("write", &serde_json::Value::Null),
+            Some("write")
+        );
+    }
+
+    #[test]
+    fn parses_bridge_requests() {
+        let placeholder = r#"{"toolCallId":"t1","toolName":"bash","input":{"command":"ls"}}"#;
+        let call =
+            ApprovalCall::parse("input", Some(APPROVAL_REQUEST_TITLE), Some(placeholder)).unwrap();
+        assert_eq!(call.tool_call_id, "t1");
+        assert_eq!(call.tool_name, "bash");
+        assert_eq!(call.input["command"], "ls");
+
+        assert!(ApprovalCall::parse("input", Some("Name?"), Some(placeholder)).is_none());
+        assert!(ApprovalCall::parse("confirm", Some(APPROVAL_REQUEST_TITLE), None).is_none());
+    }
+
+    #[tokio::test]
+    async fn gate_resolves_each_approval_once() {
+        let gate = ApprovalGate::default();
+        gate.open(ToolApproval {
+            approval_id: "a1".to_string(),
+            tool_call_id: "t1".to_string(),
+            name: "bash".to_string(),
+            input: serde_json::json!({"command": "git push"}),
+            rule: "git-push".to_string(),
+            requested_at: 1,
+            expires_at: 2,
+        })
+        .await;
+        assert_eq!(gate.list().await.len(), 1);
+        assert!(gate.take("a1").await.is_some());
+        assert!(gate.take("a1").await.is_none());
+        assert!(gate.list().await.is_empty());
+    }
+
+    #[test]
+    fn installs_bridge_extension_in_workspace() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = install_bridge_extension(dir.path()).unwrap();
+        assert_eq!(path, dir.path().join(".oqto/extensions/oqto-approvals.ts"));
+        assert!(
+            std::fs::read_to_string(&path)
+                .unwrap()
+                .contains(APPROVAL_REQUEST_TITLE)
+        );
+    }
+}
@@ -0,0 +1,265 @@
+//! Incremental output of running tools.
+//!
+//! Pi reports a running tool's output as everything produced so far
+//! (`tool_execution_update`), and for `bash` only a rolling tail of it. The
+//! runner turns those snapshots into `tool.output_delta` chunks carrying just
+//! the new text, so clients can follow a long command as it runs. Each tool
+//! call streams at most `max_bytes`; the chunk that reaches the cap is marked
+//! `truncated` and anything after it is only in `tool.end`.
+//!
+//! ```toml
+//! [runner.tool_output]
+//! enabled = true
+//! max_bytes = 1048576
+//! chunk_bytes = 16384
+//! ```
+
+use std::collections::HashMap;
+
+use oqto_protocol::events::{EventPayload, OutputChannel};
+use serde::{Deserialize, Serialize};
+
+/// Bytes of the previous snapshot used to find where a rolled-over tail
+/// continues.
+const ANCHOR_BYTES: usize = 256;
+
+/// Tool output streaming configuration (`[runner.tool_output]`).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ToolOutputConfig {
+    /// Stream output deltas instead of `tool.progress` snapshots.
+    pub enabled: bool,
+    /// Bytes streamed per tool call before the output is truncated.
+    pub max_bytes: usize,
+    /// Largest delta sent in one event.
+    pub chunk_bytes: usize,
+}
+
+impl Default for ToolOutputConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            max_bytes: 1024 * 1024,
+            chunk_bytes: 16 * 1024,
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct CallStream {
+    next_seq: u64,
+    sent_bytes: usize,
+    /// Last snapshot per channel.
+    last: HashMap<OutputChannel, String>,
+    truncated: bool,
+}
+
+/// Delta state of the running tool calls of one session.
+#[derive(Debug)]
+pub struct ToolOutputStreams {
+    config: ToolOutputConfig,
+    calls: HashMap<String, CallStream>,
+}
+
+impl ToolOutputStreams {
+    /// None when streaming is disabled.
+    pub fn new(config: &ToolOutputConfig) -> Option<Self> {
+        if !config.enabled || config.max_bytes == 0 {
+            return None;
+        }
+        Some(Self {
+            config: ToolOutputConfig {
+                chunk_bytes: config.chunk_bytes.max(1),
+                ..config.clone()
+            },
+            calls: HashMap::new(),
+        })
+    }
+
+    /// Delta events for a new output snapshot of a tool call.
+    pub fn update(
+        &mut self,
+        tool_call_id: &str,
+        name: &str,
+        channel: OutputChannel,
+        snapshot: &str,
+    ) -> Vec<EventPayload> {
+        let call = self.calls.entry(tool_call_id.to_string()).or_default();
+        if call.truncated {
+            return Vec::new();
+        }
+        let previous = call.last.get(&channel).map(String::as_str).unwrap_or("");
+        let mut new = new_output(previous, snapshot);
+        call.last.insert(channel, snapshot.to_string());
+        if new.is_empty() {
+            return Vec::new();
+        }
+
+        let remaining = self.config.max_bytes - call.sent_bytes;
+        if new.len() >= remaining {
+            new = &new[..floor_char_boundary(new, remaining)];
+            call.truncated = true;
+        }
+
+        let mut events = Vec::new();
+        let mut rest = new;
+        loop {
+            let split = floor_char_boundary(rest, self.config.chunk_bytes.min(rest.len()));
+            // A chunk smaller than one character would never advance.
+            let split = if split == 0 && !rest.is_empty() {
+                rest.chars().next().map_or(rest.len(), char::len_utf8)
+            } else {
+                split
+            };
+            let (chunk, tail) = rest.split_at(split);
+            rest = tail;
+            call.sent_bytes += chunk.len();
+            events.push(EventPayload::ToolOutputDelta {
+                tool_call_id: tool_call_id.to_string(),
+                name: name.to_string(),
+                channel,
+                seq: call.next_seq,
+                delta: chunk.to_string(),
+                truncated: call.truncated && rest.is_empty(),
+            });
+            call.next_seq += 1;
+            if rest.is_empty() {
+                break;
+            }
+        }
+        events
+    }
+
+    /// Forget a finished tool call.
+    pub fn finish(&mut self, tool_call_id: &str) {
+        self.calls.remove(tool_call_id);
+    }
+}
+
+/// Text of `current` that was not in `previous`.
+///
+/// Snapshots normally extend the previous one. When the producer keeps only
+/// a tail, the end of the previous snapshot is looked up in the new one and
+/// what follows it is new; if no overlap is found the whole snapshot is.
+fn new_output<'a>(previous: &str, current: &'a str) -> &'a str {
+    if let Some(rest) = current.strip_prefix(previous) {
+        return rest;
+    }
+    let start = ceil_char_boundary(previous, previous.len().saturating_sub(ANCHOR_BYTES));
+    let anchor = &previous[start..];
+    if !anchor.is_empty()
+        && let Some(pos) = current.rfind(anchor)
+    {
+        return &current[pos + anchor.len()..];
+    }
+    // The kept tail can be shorter than the anchor.
+    anchor
+        .char_indices()
+        .skip(1)
+        .find_map(|(i, _)| current.strip_prefix(&anchor[i..]))
+        .unwrap_or(current)
+}
+
+fn floor_char_boundary(s: &str, mut index: usize) -> usize {
+    index = index.min(s.len());
+    while !s.is_char_boundary(index) {
+        index -= 1;
+    }
+    index
+}
+
+fn ceil_char_boundary(s: &str, mut index: usize) -> usize {
+    while !s.is_char_boundary(index) {
+        index += 1;
+    }
+    index
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn deltas(events: &[EventPayload]) -> Vec<(u64, &str, bool)> {
+        events
+            .iter()
+            .map(|e| match e {
+                EventPayload::ToolOutputDelta {
+                    seq,
+                    delta,
+                    truncated,
+                    ..
+                } => (*seq, delta.as_str(), *truncated),
+                other => panic!("unexpected event {other:?}"),
+            })
+            .collect()
+    }
+
+    fn streams(max_bytes: usize, chunk_bytes: usize) -> ToolOutputStreams {
+        ToolOutputStreams::new(&ToolOutputConfig {
+            enabled: true,
+            max_bytes,
+            chunk_bytes,
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn test_snapshots_become_deltas() {
+        let mut s = streams(1024, 1024);
+        let out = OutputChannel::Stdout;
+        assert_eq!(
+            deltas(&s.update("c1", "bash", out, "running 3 tests\n")),
+            vec![(0, "running 3 tests\n", false)]
+        );
+        assert!(s.update("c1", "bash", out, "running 3 tests\n").is_empty());
+        assert_eq!(
+            deltas(&s.update("c1", "bash", out, "running 3 tests\ntest a ... ok\n")),
+            vec![(1, "test a ... ok\n", false)]
+        );
+        assert_eq!(
+            deltas(&s.update("c1", "bash", OutputChannel::Stderr, "warning\n")),
+            vec![(2, "warning\n", false)]
+        );
+    }
+
+    #[test]
+    fn test_rolling_tail_is_followed() {
+        assert_eq!(
+            new_output("line 1\nline 2\n", "line 2\nline 3\n"),
+            "line 3\n"
+        );
+        assert_eq!(new_output("abc", "xyz"), "xyz");
+    }
+
+    #[test]
+    fn test_chunks_and_truncation() {
+        let mut s = streams(10, 4);
+        let out = OutputChannel::Stdout;
+        assert_eq!(
+            deltas(&s.update("c1", "bash", out, "abcdef")),
+            vec![(0, "abcd", false), (1, "ef", false)]
+        );
+        assert_eq!(
+            deltas(&s.update("c1", "bash", out, "abcdefghijklmn")),
+            vec![(2, "ghij", true)]
+        );
+        assert!(s.update("c1", "bash", out, "abcdefghijklmnop").is_empty());
+
+        s.finish("c1");
+        assert_eq!(
+            deltas(&s.update("c1", "bash", out, "é")),
+            vec![(0, "é", false)]
+        );
+    }
+
+    #[test]
+    fn test_disabled() {
+        assert!(
+            ToolOutputStreams::new(&ToolOutputConfig {
+                enabled: false,
+                ..Default::default()
+            })
+            .is_none()
+        );
+    }
+}
@@ -0,0 +1,266 @@
+//! Per-session rate limits for agent tool calls.
+//!
+//! Pi executes tools without asking the runner first (`--approve`), so limits
+//! are enforced when `tool_execution_start` is observed: the call is counted
+//! against its rule's sliding window and, once the window is full, the runner
+//! emits `tool.rate_limited` and steers the agent with a backoff hint. An
+//! agent that ignores the hint and keeps calling has its turn aborted.
+//!
+//! ```toml
+//! [runner.tool_rate_limits]
+//! abort_after = 5
+//!
+//! [[runner.tool_rate_limits.rules]]
+//! name = "bash"
+//! tools = ["bash"]
+//! max_calls = 20
+//! window_secs = 60
+//! ```
+
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+
+/// Tool rate limit configuration (`[runner.tool_rate_limits]`).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ToolRateLimitConfig {
+    pub enabled: bool,
+    /// Calls over the limit, after the backoff hint, before the agent's turn
+    /// is aborted. 0 never aborts.
+    pub abort_after: u32,
+    /// Limits by tool class. A tool counts against the first rule listing it.
+    pub rules: Vec<ToolRateRule>,
+}
+
+impl Default for ToolRateLimitConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            abort_after: 5,
+            rules: vec![
+                ToolRateRule {
+                    name: "bash".to_string(),
+                    tools: vec!["bash".to_string()],
+                    max_calls: 20,
+                    window_secs: 60,
+                },
+                ToolRateRule {
+                    name: "network".to_string(),
+                    tools: vec![
+                        "fetch".to_string(),
+                        "web_fetch".to_string(),
+                        "web_search".to_string(),
+                    ],
+                    max_calls: 5,
+                    window_secs: 60,
+                },
+            ],
+        }
+    }
+}
+
+/// Limit for one class of tools.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ToolRateRule {
+    /// Reported in `tool.rate_limited` events.
+    pub name: String,
+    /// Tool names covered by this rule (case-insensitive).
+    pub tools: Vec<String>,
+    pub max_calls: u32,
+    pub window_secs: u64,
+}
+
+impl ToolRateRule {
+    fn matches(&self, tool_name: &str) -> bool {
+        self.tools.iter().any(|t| t.eq_ignore_ascii_case(tool_name))
+    }
+}
+
+/// A tool call that went over its rule's limit.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RateLimitHit {
+    pub rule: String,
+    pub max_calls: u32,
+    pub window_secs: u64,
+    /// Until the rule's window has room for another call.
+    pub retry_after: Duration,
+    /// First call over the limit in this burst; steer the agent.
+    pub send_hint: bool,
+    /// The agent ignored the hint; abort its turn.
+    pub abort: bool,
+}
+
+impl RateLimitHit {
+    /// Steering message asking the agent to back off.
+    pub fn hint(&self, tool_name: &str) -> String {
+        format!(
+            "[oqto] Tool rate limit reached: `{tool_name}` calls ({rule}) are limited to {max} \
+             per {window}s in this session. Wait about {wait}s before the next one, and batch \
+             work into fewer calls where possible.",
+            rule = self.rule,
+            max = self.max_calls,
+            window = self.window_secs,
+            wait = self.retry_after.as_secs().max(1),
+        )
+    }
+}
+
+#[derive(Debug, Default)]
+struct RuleWindow {
+    calls: VecDeque<Instant>,
+    /// Calls over the limit since the window last had room.
+    over: u32,
+}
+
+/// Sliding-window tool call counter for one session.
+#[derive(Debug)]
+pub struct ToolRateLimiter {
+    config: ToolRateLimitConfig,
+    windows: Vec<RuleWindow>,
+}
+
+impl ToolRateLimiter {
+    /// None when limits are disabled or no rules are configured.
+    pub fn new(config: &ToolRateLimitConfig) -> Option<Self> {
+        if !config.enabled || config.rules.is_empty() {
+            return None;
+        }
+        Some(Self {
+            windows: config.rules.iter().map(|_| RuleWindow::default()).collect(),
+            config: config.clone(),
+        })
+    }
+
+    /// Record a call to `tool_name` at `now`.
+    pub fn check(&mut self, tool_name: &str, now: Instant) -> Option<RateLimitHit> {
+        let idx = self
+            .config
+            .rules
+            .iter()
+            .position(|r| r.matches(tool_name))?;
+        let rule = &self.config.rules[idx];
+        let window = Duration::from_secs(rule.window_secs.max(1));
+        let state = &mut self.windows[idx];
+
+        while state
+            .calls
+            .front()
+            .is_some_and(|t| now.duration_since(*t) >= window)
+        {
+            state.calls.pop_front();
+        }
+        state.calls.push_back(now);
+
+        let max = rule.max_calls as usize;
+        if state.calls.len() <= max {
+            state.over = 0;
+            return None;
+        }
+
+        state.over += 1;
+        // The next call fits once all but `max - 1` of the recorded calls
+        // have left the window.
+        let freeing = state.calls[state.calls.len() - max.max(1)];
+        let retry_after = (freeing + window).saturating_duration_since(now);
+        let abort = self.config.abort_after > 0 && state.over > self.config.abort_after;
+        let send_hint = state.over == 1;
+        if abort {
+            // Start over with a fresh hint once the agent resumes.
+            state.over = 0;
+        }
+
+        Some(RateLimitHit {
+            rule: rule.name.clone(),
+            max_calls: rule.max_calls,
+            window_secs: rule.window_secs,
+            retry_after,
+            send_hint,
+            abort,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(max_calls: u32, abort_after: u32) -> ToolRateLimitConfig {
+        ToolRateLimitConfig {
+            enabled: true,
+            abort_after,
+            rules: vec![ToolRateRule {
+                name: "bash".to_string(),
+                tools: vec!["bash".to_string()],
+                max_calls,
+                window_secs: 60,
+            }],
+        }
+    }
+
+    #[test]
+    fn limits_calls_per_window() {
+        let mut limiter = ToolRateLimiter::new(&config(2, 0)).unwrap();
+        let start = Instant::now();
+
+        assert!(limiter.check("bash", start).is_none());
+        assert!(
+            limiter
+                .check("Bash", start + Duration::from_secs(10))
+                .is_none()
+        );
+        assert!(
+            limiter
+                .check("read", start + Duration::from_secs(11))
+                .is_none()
+        );
+
+        let hit = limiter
+            .check("bash", start + Duration::from_secs(20))
+            .unwrap();
+        assert_eq!(hit.rule, "bash");
+        assert!(hit.send_hint);
+        assert!(!hit.abort);
+        // Room for another call once the 10s call expires at 70s.
+        assert_eq!(hit.retry_after, Duration::from_secs(50));
+
+        let again = limiter
+            .check("bash", start + Duration::from_secs(21))
+            .unwrap();
+        assert!(!again.send_hint);
+
+        // Every earlier call has left the window.
+        assert!(
+            limiter
+                .check("bash", start + Duration::from_secs(81))
+                .is_none()
+        );
+    }
+
+    #[test]
+    fn aborts_when_hint_is_ignored() {
+        let mut limiter = ToolRateLimiter::new(&config(1, 2)).unwrap();
+        let now = Instant::now();
+
+        assert!(limiter.check("bash", now).is_none());
+        let hits: Vec<_> = (0..4)
+            .map(|_| limiter.check("bash", now).unwrap())
+            .collect();
+        assert_eq!(
+            hits.iter().map(|h| h.abort).collect::<Vec<_>>(),
+            vec![false, false, true, false]
+        );
+        assert!(hits[3].send_hint);
+    }
+
+    #[test]
+    fn disabled_without_rules() {
+        let mut cfg = config(1, 0);
+        cfg.rules.clear();
+        assert!(ToolRateLimiter::new(&cfg).is_none());
+        cfg = config(1, 0);
+        cfg.enabled = false;
+        assert!(ToolRateLimiter::new(&cfg).is_none());
+    }
+}
@@ -0,0 +1,426 @@
+//! Workspaces bootstrapped from uploaded archives.
+//!
+//! The backend hands the runner a zip or tar.gz and a directory that does
+//! not exist yet. Extracting in the runner writes the files as the workspace
+//! owner, so they get the right ownership without root. Everything goes into
+//! a hidden staging directory next to the target first and is renamed into
+//! place once the whole archive is written, so a failed upload leaves
+//! nothing behind. Entries that would land outside the target (absolute
+//! paths, `..`), links and special files are skipped, and the extracted
+//! bytes and number of files are capped. An archive whose entries all sit
+//! under one top-level directory, as source downloads from forges do, is
+//! unwrapped.
+
+use std::io::Read;
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Component, Path, PathBuf};
+
+use crate::protocol::ArchiveFormat;
+
+/// Project files looked for at the root of an extracted workspace.
+const MANIFESTS: &[&str] = &[
+    ".oqto/workspace.toml",
+    "AGENTS.md",
+    "package.json",
+    "Cargo.toml",
+    "pyproject.toml",
+    "requirements.txt",
+    "go.mod",
+    "Gemfile",
+    "pom.xml",
+    "build.gradle",
+    "composer.json",
+    "Makefile",
+    "Dockerfile",
+];
+
+/// Caps on what one archive may extract to.
+#[derive(Debug, Clone, Copy)]
+pub struct ExtractLimits {
+    pub max_bytes: u64,
+    pub max_files: u64,
+}
+
+/// What an extraction wrote.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ExtractReport {
+    pub files: u64,
+    pub bytes: u64,
+    /// Entries skipped for unsafe paths or unsupported types.
+    pub skipped: u64,
+    /// Entries of [`MANIFESTS`] present in the workspace.
+    pub manifests: Vec<String>,
+}
+
+/// Why an archive was not extracted.
+#[derive(Debug)]
+pub enum ExtractError {
+    /// The target directory already exists.
+    Exists(PathBuf),
+    /// Not a readable archive of the given format.
+    Invalid(String),
+    /// The archive extracts to more than the limits allow.
+    TooLarge(String),
+    Io(std::io::Error),
+}
+
+impl std::fmt::Display for ExtractError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Exists(path) => write!(f, "{} already exists", path.display()),
+            Self::Invalid(reason) => write!(f, "invalid archive: {reason}"),
+            Self::TooLarge(reason) => write!(f, "archive too large: {reason}"),
+            Self::Io(e) => write!(f, "extracting archive: {e}"),
+        }
+    }
+}
+
+impl std::error::Error for ExtractError {}
+
+impl From<std::io::Error> for ExtractError {
+    fn from(e: std::io::Error) -> Self {
+        Self::Io(e)
+    }
+}
+
+/// Extract `data` into the new directory `dest`.
+pub fn extract(
+    data: &[u8],
+    format: ArchiveFormat,
+    dest: &Path,
+    limits: ExtractLimits,
+) -> Result<ExtractReport, ExtractError> {
+    if dest.exists() {
+        return Err(ExtractError::Exists(dest.to_path_buf()));
+    }
+    let (Some(parent), Some(name)) = (dest.parent(), dest.file_name()) else {
+        return Err(ExtractError::Invalid(format!(
+            "{} is not a directory path",
+            dest.display()
+        )));
+    };
+    std::fs::create_dir_all(parent)?;
+    let staging = parent.join(format!(
+        ".{}.extracting-{}",
+        name.to_string_lossy(),
+        uuid::Uuid::new_v4().simple()
+    ));
+    std::fs::create_dir(&staging)?;
+
+    let result = extract_into(data, format, &staging, limits).and_then(|mut report| {
+        match std::fs::rename(&staging, dest) {
+            Ok(()) => {}
+            // Another upload won the race.
+            Err(_) if dest.exists() => return Err(ExtractError::Exists(dest.to_path_buf())),
+            Err(e) => return Err(e.into()),
+        }
+        report.manifests = MANIFESTS
+            .iter()
+            .filter(|manifest| dest.join(manifest).is_file())
+            .map(|manifest| manifest.to_string())
+            .collect();
+        Ok(report)
+    });
+    if result.is_err() {
+        let _ = std::fs::remove_dir_all(&staging);
+    }
+    result
+}
+
+fn extract_into(
+    data: &[u8],
+    format: ArchiveFormat,
+    root: &Path,
+    limits: ExtractLimits,
+) -> Result<ExtractReport, ExtractError> {
+    match format {
+        ArchiveFormat::Zip => extract_zip(data, root, limits),
+        ArchiveFormat::TarGz => extract_tar_gz(data, root, limits),
+    }
+}
+
+fn extract_zip(
+    data: &[u8],
+    root: &Path,
+    limits: ExtractLimits,
+) -> Result<ExtractReport, ExtractError> {
+    let invalid = |e: zip::result::ZipError| ExtractError::Invalid(e.to_string());
+    let mut zip = zip::ZipArchive::new(std::io::Cursor::new(data)).map_err(invalid)?;
+    let entries = (0..zip.len())
+        .map(|i| {
+            zip.by_index_raw(i)
+                .map(|file| (PathBuf::from(file.name()), file.is_dir()))
+        })
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(invalid)?;
+    let mut out = Writer::new(root, single_root(&entries), limits);
+    for i in 0..zip.len() {
+        let mut file = zip.by_index(i).map_err(invalid)?;
+        let name = PathBuf::from(file.name());
+        let mode = file.unix_mode();
+        if file.is_dir() {
+            out.dir(&name)?;
+        } else if mode.is_some_and(|mode| mode & 0o170000 == 0o120000) {
+            // Symlink.
+            out.report.skipped += 1;
+        } else {
+            out.file(&name, &mut file, mode)?;
+        }
+    }
+    Ok(out.report)
+}
+
+fn extract_tar_gz(
+    data: &[u8],
+    root: &Path,
+    limits: ExtractLimits,
+) -> Result<ExtractReport, ExtractError> {
+    let invalid = |e: std::io::Error| ExtractError::Invalid(e.to_string());
+    let open = || tar::Archive::new(flate2::read::GzDecoder::new(data));
+
+    // A first pass over the headers finds a common top-level directory.
+    let mut archive = open();
+    let mut entries = Vec::new();
+    for entry in archive.entries().map_err(invalid)? {
+        let entry = entry.map_err(invalid)?;
+        let is_dir = entry.header().entry_type().is_dir();
+        entries.push((entry.path().map_err(invalid)?.into_owned(), is_dir));
+    }
+
+    let mut out = Writer::new(root, single_root(&entries), limits);
+    let mut archive = open();
+    for entry in archive.entries().map_err(invalid)? {
+        let mut entry = entry.map_err(invalid)?;
+        let name = entry.path().map_err(invalid)?.into_owned();
+        let kind = entry.header().entry_type();
+        if kind.is_dir() {
+            out.dir(&name)?;
+        } else if kind.is_file() || kind.is_contiguous() {
+            let mode = entry.header().mode().ok();
+            out.file(&name, &mut entry, mode)?;
+        } else {
+            out.report.skipped += 1;
+        }
+    }
+    Ok(out.report)
+}
+
+/// `path` as plain components below the target, or None when it is
+/// absolute or climbs out with `..`.
+fn safe_relative(path: &Path) -> Option<PathBuf> {
+    let mut relative = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::Normal(part) => relative.push(part),
+            Component::CurDir => {}
+            Component::ParentDir | Component::RootDir | Component::Prefix(_) => return None,
+        }
+    }
+    (!relative.as_os_str().is_empty()).then_some(relative)
+}
+
+/// The one top-level directory all entries (path, is directory) are under,
+/// if there is one.
+fn single_root(entries: &[(PathBuf, bool)]) -> Option<PathBuf> {
+    let mut root: Option<PathBuf> = None;
+    for (name, is_dir) in entries {
+        let Some(relative) = safe_relative(name) else {
+            continue;
+        };
+        let mut components = relative.components();
+        let first = PathBuf::from(components.next()?.as_os_str());
+        if components.next().is_none() && !is_dir {
+            return None;
+        }
+        match &root {
+            Some(root) if *root != first => return None,
+            Some(_) => {}
+            None => root = Some(first),
+        }
+    }
+    root
+}
+
+/// Writes entries below `root`, counting against the limits.
+struct Writer<'a> {
+    root: &'a Path,
+    strip: Option<PathBuf>,
+    limits: ExtractLimits,
+    report: ExtractReport,
+}
+
+impl<'a> Writer<'a> {
+    fn new(root: &'a Path, strip: Option<PathBuf>, limits: ExtractLimits) -> Self {
+        Self {
+            root,
+            strip,
+            limits,
+            report: ExtractReport::default(),
+        }
+    }
+
+    /// Where an entry goes, or None when it is skipped.
+    fn target(&mut self, name: &Path) -> Option<PathBuf> {
+        let Some(relative) = safe_relative(name) else {
+            self.report.skipped += 1;
+            return None;
+        };
+        match &self.strip {
+            Some(prefix) => relative
+                .strip_prefix(prefix)
+                .ok()
+                .filter(|rest| !rest.as_os_str().is_empty())
+                .map(|rest| self.root.join(rest)),
+            None => Some(self.root.join(relative)),
+        }
+    }
+
+    fn dir(&mut self, name: &Path) -> Result<(), ExtractError> {
+        if let Some(target) = self.target(name) {
+            std::fs::create_dir_all(target)?;
+        }
+        Ok(())
+    }
+
+    fn file(
+        &mut self,
+        name: &Path,
+        reader: &mut impl Read,
+        mode: Option<u32>,
+    ) -> Result<(), ExtractError> {
+        let Some(target) = self.target(name) else {
+            return Ok(());
+        };
+        if self.report.files >= self.limits.max_files {
+            return Err(ExtractError::TooLarge(format!(
+                "more than {} files",
+                self.limits.max_files
+            )));
+        }
+        if let Some(parent) = target.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let remaining = self.limits.max_bytes - self.report.bytes;
+        let mut out = std::fs::File::create(&target)?;
+        let written = std::io::copy(&mut reader.take(remaining + 1), &mut out)
+            .map_err(|e| ExtractError::Invalid(e.to_string()))?;
+        if written > remaining {
+            return Err(ExtractError::TooLarge(format!(
+                "more than {} bytes",
+                self.limits.max_bytes
+            )));
+        }
+        if let Some(mode) = mode {
+            // No setuid bits or group/world write from an upload.
+            std::fs::set_permissions(&target, std::fs::Permissions::from_mode(mode & 0o755))?;
+        }
+        self.report.files += 1;
+        self.report.bytes += written;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    const LIMITS: ExtractLimits = ExtractLimits {
+        max_bytes: 1024,
+        max_files: 10,
+    };
+
+    fn zip(entries: &[(&str, &[u8])]) -> Vec<u8> {
+        let mut out = zip::ZipWriter::new(std::io::Cursor::new(Vec::new()));
+        for (name, data) in entries {
+            out.start_file(*name, zip::write::SimpleFileOptions::default())
+                .unwrap();
+            out.write_all(data).unwrap();
+        }
+        out.finish().unwrap().into_inner()
+    }
+
+    fn tar_gz(entries: &[(&str, &[u8])]) -> Vec<u8> {
+        let gz = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::fast());
+        let mut builder = tar::Builder::new(gz);
+        for (name, data) in entries {
+            let mut header = tar::Header::new_gnu();
+            header.set_size(data.len() as u64);
+            header.set_mode(0o4777);
+            header.set_entry_type(tar::EntryType::Regular);
+            // Bypass the builder's own path checks to write `..` entries.
+            header.as_gnu_mut().unwrap().name[..name.len()].copy_from_slice(name.as_bytes());
+            header.set_cksum();
+            builder.append(&header, *data).unwrap();
+        }
+        builder.into_inner().unwrap().finish().unwrap()
+    }
+
+    #[test]
+    fn zip_entries_outside_the_target_are_skipped() {
+        let dir = tempfile::tempdir().unwrap();
+        let dest = dir.path().join("root/project");
+        let data = zip(&[
+            ("project-main/package.json", b"{}"),
+            ("project-main/src/index.js", b"console.log(1)"),
+            ("project-main/../../escape.txt", b"nope"),
+            ("/etc/passwd", b"nope"),
+        ]);
+
+        let report = extract(&data, ArchiveFormat::Zip, &dest, LIMITS).unwrap();
+        assert_eq!(report.files, 2);
+        assert_eq!(report.skipped, 2);
+        assert_eq!(report.manifests, vec!["package.json"]);
+        assert_eq!(
+            std::fs::read_to_string(dest.join("src/index.js")).unwrap(),
+            "console.log(1)"
+        );
+        assert!(!dir.path().join("escape.txt").exists());
+        assert!(!dir.path().join("root/escape.txt").exists());
+
+        // The target must be new.
+        assert!(matches!(
+            extract(&data, ArchiveFormat::Zip, &dest, LIMITS),
+            Err(ExtractError::Exists(_))
+        ));
+    }
+
+    #[test]
+    fn tar_gz_over_the_limits_leaves_nothing_behind() {
+        let dir = tempfile::tempdir().unwrap();
+        let dest = dir.path().join("project");
+        let data = tar_gz(&[
+            ("README.md", b"hi"),
+            ("../up.txt", b"nope"),
+            ("run.sh", b"x"),
+        ]);
+
+        let report = extract(&data, ArchiveFormat::TarGz, &dest, LIMITS).unwrap();
+        assert_eq!((report.files, report.bytes, report.skipped), (2, 3, 1));
+        let mode = std::fs::metadata(dest.join("run.sh"))
+            .unwrap()
+            .permissions()
+            .mode();
+        assert_eq!(mode & 0o7777, 0o755);
+        assert!(!dir.path().join("up.txt").exists());
+
+        let big = tar_gz(&[("a.bin", &[0; 600]), ("b.bin", &[0; 600])]);
+        let err = extract(&big, ArchiveFormat::TarGz, &dir.path().join("big"), LIMITS);
+        assert!(matches!(err, Err(ExtractError::TooLarge(_))));
+        let left: Vec<_> = std::fs::read_dir(dir.path())
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name())
+            .collect();
+        assert_eq!(left, vec![std::ffi::OsString::from("project")]);
+
+        assert!(matches!(
+            extract(
+                b"not an archive",
+                ArchiveFormat::TarGz,
+                &dir.path().join("x"),
+                LIMITS
+            ),
+            Err(ExtractError::Invalid(_))
+        ));
+    }
+}
@@ -1,31 +1,50 @@
 //! `oqto-egress-relay` -- the in-namespace egress forwarder for
-//! `NetworkMode::Proxy`.
+//! `NetworkMode::Proxy` and for containers with egress rules.
 //!
-//! Runs inside an agent's network namespace (spawned there by the runner). The
-//! namespace's nftables rules DNAT the agent's TCP egress to this relay's listen
-//! address. For each connection the relay:
+//! Runs inside an agent's network namespace (spawned there by the runner or the
+//! backend). The namespace's nftables rules DNAT the agent's TCP egress to this
+//! relay's listen address. For each connection the relay:
 //!   1. reads the original destination via `SO_ORIGINAL_DST` (works here because
 //!      the DNAT and its conntrack entry are in this same namespace),
-//!   2. connects to eavs at the configured transparent endpoint,
-//!   3. writes a PROXY protocol v2 header announcing the original destination,
+//!   2. checks it against the egress policy, reading the host from the first
+//!      bytes when the policy names domains; blocked connections are closed and
+//!      reported on stdout,
+//!   3. connects to eavs at the configured transparent endpoint and writes a
+//!      PROXY protocol v2 header announcing the original destination, or
+//!      without eavs connects to the destination itself (marked `RELAY_MARK`),
 //!   4. splices bytes in both directions.
 //!
-//! It is pure mechanism: no ACL, no secrets. Policy lives in eavs.
+//! It carries no secrets; credential injection lives in eavs.
 //!
 //! Configuration is via env vars set by the spawner:
-//!   - `OQTO_EGRESS_RELAY_LISTEN` -- `ip:port` to listen on (in the namespace)
-//!   - `OQTO_EGRESS_RELAY_EAVS`   -- `ip:port` of the eavs transparent listener
+//!   - `OQTO_EGRESS_RELAY_LISTEN`    -- `ip:port` to listen on (in the namespace)
+//!   - `OQTO_EGRESS_RELAY_EAVS`      -- `ip:port` of the eavs transparent listener
+//!   - `OQTO_EGRESS_RELAY_POLICY`    -- the egress policy as JSON (optional)
+//!   - `OQTO_EGRESS_RELAY_WATCH_PID` -- exit once this pid is gone (optional)
 
-use std::io;
+use std::io::{self, Read, Write};
 use std::net::{Ipv4Addr, SocketAddr, SocketAddrV4, TcpListener, TcpStream};
-use std::os::fd::{AsRawFd, RawFd};
+use std::os::fd::{AsRawFd, FromRawFd, OwnedFd, RawFd};
+use std::sync::Arc;
 use std::thread;
+use std::time::Duration;
 
-use oqto_sandbox::egress_relay::{RELAY_EAVS_ENV, RELAY_LISTEN_ENV, proxy_v2_header};
+use oqto_sandbox::egress_policy::{MAX_SNIFF_BYTES, RELAY_POLICY_ENV, sniff_host};
+use oqto_sandbox::egress_relay::{
+    RELAY_EAVS_ENV, RELAY_LISTEN_ENV, RELAY_MARK, RELAY_WATCH_PID_ENV, proxy_v2_header,
+};
+use oqto_sandbox::{BlockedConnection, EgressPolicy};
 
 /// netfilter `SO_ORIGINAL_DST` (not exported by the `libc` crate).
 const SO_ORIGINAL_DST: libc::c_int = 80;
 
+/// How long to wait for the first bytes of a connection when its host is
+/// needed. Protocols where the server speaks first never send them.
+const SNIFF_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// How often the watched pid is checked.
+const WATCH_INTERVAL: Duration = Duration::from_secs(2);
+
 fn main() {
     if let Err(e) = run() {
         eprintln!("oqto-egress-relay: fatal: {e}");
@@ -35,18 +54,42 @@ fn main() {
 
 fn run() -> io::Result<()> {
     let listen = env_addr(RELAY_LISTEN_ENV)?;
-    let eavs = std::env::var(RELAY_EAVS_ENV)
-        .map_err(|_| io::Error::other(format!("{RELAY_EAVS_ENV} not set")))?;
+    let eavs = std::env::var(RELAY_EAVS_ENV).ok();
+    let policy: EgressPolicy = match std::env::var(RELAY_POLICY_ENV) {
+        Ok(raw) => serde_json::from_str(&raw)
+            .map_err(|e| io::Error::other(format!("{RELAY_POLICY_ENV} is invalid: {e}")))?,
+        Err(_) => EgressPolicy::default(),
+    };
+    let policy = Arc::new(policy);
+    if let Ok(raw) = std::env::var(RELAY_WATCH_PID_ENV) {
+        let pid: u32 = raw
+            .parse()
+            .map_err(|_| io::Error::other(format!("{RELAY_WATCH_PID_ENV} is not a pid: {raw}")))?;
+        thread::spawn(move || watch_pid(pid));
+    }
+    // Reports of blocked connections are dropped rather than stalling
+    // connections while nobody reads them.
+    // SAFETY: fcntl on the stdout fd, which stays open for the process lifetime.
+    unsafe {
+        let flags = libc::fcntl(1, libc::F_GETFL);
+        if flags != -1 {
+            libc::fcntl(1, libc::F_SETFL, flags | libc::O_NONBLOCK);
+        }
+    }
 
     let listener = TcpListener::bind(listen)?;
-    eprintln!("oqto-egress-relay: listening on {listen} -> eavs {eavs}");
+    match &eavs {
+        Some(eavs) => eprintln!("oqto-egress-relay: listening on {listen} -> eavs {eavs}"),
+        None => eprintln!("oqto-egress-relay: listening on {listen} -> direct"),
+    }
 
     for conn in listener.incoming() {
         match conn {
             Ok(stream) => {
                 let eavs = eavs.clone();
+                let policy = Arc::clone(&policy);
                 thread::spawn(move || {
-                    if let Err(e) = handle(stream, &eavs) {
+                    if let Err(e) = handle(stream, eavs.as_deref(), &policy) {
                         eprintln!("oqto-egress-relay: connection error: {e}");
                     }
                 });
@@ -57,24 +100,141 @@ fn run() -> io::Result<()> {
     Ok(())
 }
 
-fn handle(inbound: TcpStream, eavs: &str) -> io::Result<()> {
+fn handle(mut inbound: TcpStream, eavs: Option<&str>, policy: &EgressPolicy) -> io::Result<()> {
     let dst = original_dst(inbound.as_raw_fd())?;
     let src = inbound
         .peer_addr()
         .unwrap_or(SocketAddr::V4(SocketAddrV4::new(Ipv4Addr::UNSPECIFIED, 0)));
 
-    let mut outbound = TcpStream::connect(eavs)?;
-    // Announce the real destination to eavs before any payload.
-    let header = proxy_v2_header(src, SocketAddr::V4(dst));
-    {
-        use std::io::Write;
-        outbound.write_all(&header)?;
+    // Bytes read to find the host, forwarded ahead of the rest.
+    let mut head = Vec::new();
+    let host = if policy.needs_host() {
+        read_host(&mut inbound, &mut head)?
+    } else {
+        None
+    };
+    if let Err(reason) = policy.check(host.as_deref(), dst.port()) {
+        report(BlockedConnection {
+            host,
+            address: dst.ip().to_string(),
+            port: dst.port(),
+            reason,
+        });
+        return Ok(());
     }
 
+    let mut outbound = match eavs {
+        Some(eavs) => {
+            let mut outbound = TcpStream::connect(eavs)?;
+            // Announce the real destination to eavs before any payload.
+            outbound.write_all(&proxy_v2_header(src, SocketAddr::V4(dst)))?;
+            outbound
+        }
+        None => connect_marked(dst)?,
+    };
+    outbound.write_all(&head)?;
+
     splice(inbound, outbound);
     Ok(())
 }
 
+/// Read the first bytes of `inbound` into `head` until the host can be read
+/// from them, they cannot carry one, or the peer goes quiet.
+fn read_host(inbound: &mut TcpStream, head: &mut Vec<u8>) -> io::Result<Option<String>> {
+    inbound.set_read_timeout(Some(SNIFF_TIMEOUT))?;
+    let mut buf = [0u8; 4096];
+    let host = loop {
+        if let Some(host) = sniff_host(head) {
+            break Some(host);
+        }
+        // TLS records start with 0x16, HTTP/1 requests with a method.
+        let may_carry_host = head
+            .first()
+            .is_none_or(|b| *b == 0x16 || b.is_ascii_uppercase());
+        if !may_carry_host || head.len() >= MAX_SNIFF_BYTES {
+            break None;
+        }
+        match inbound.read(&mut buf) {
+            Ok(0) => break None,
+            Ok(n) => head.extend_from_slice(&buf[..n]),
+            Err(e)
+                if matches!(
+                    e.kind(),
+                    io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut
+                ) =>
+            {
+                break None;
+            }
+            Err(e) => return Err(e),
+        }
+    };
+    inbound.set_read_timeout(None)?;
+    Ok(host)
+}
+
+/// Write a blocked connection to stdout as a JSON line, best effort.
+fn report(blocked: BlockedConnection) {
+    eprintln!(
+        "oqto-egress-relay: blocked {}:{} ({}): {}",
+        blocked.address,
+        blocked.port,
+        blocked.host.as_deref().unwrap_or("unknown host"),
+        blocked.reason
+    );
+    if let Ok(line) = serde_json::to_string(&blocked) {
+        let _ = writeln!(io::stdout().lock(), "{line}");
+    }
+}
+
+/// Connect to `dst` with `SO_MARK` set to [`RELAY_MARK`], so the connection
+/// passes the namespace's redirect.
+fn connect_marked(dst: SocketAddrV4) -> io::Result<TcpStream> {
+    // SAFETY: plain socket syscalls; the fd is owned by `OwnedFd` right away so
+    // it is closed on every error path.
+    unsafe {
+        let fd = libc::socket(libc::AF_INET, libc::SOCK_STREAM | libc::SOCK_CLOEXEC, 0);
+        if fd == -1 {
+            return Err(io::Error::last_os_error());
+        }
+        let fd = OwnedFd::from_raw_fd(fd);
+        let mark = RELAY_MARK;
+        if libc::setsockopt(
+            fd.as_raw_fd(),
+            libc::SOL_SOCKET,
+            libc::SO_MARK,
+            &mark as *const u32 as *const libc::c_void,
+            std::mem::size_of::<u32>() as libc::socklen_t,
+        ) != 0
+        {
+            return Err(io::Error::last_os_error());
+        }
+        let mut addr: libc::sockaddr_in = std::mem::zeroed();
+        addr.sin_family = libc::AF_INET as libc::sa_family_t;
+        addr.sin_port = dst.port().to_be();
+        addr.sin_addr.s_addr = u32::from(*dst.ip()).to_be();
+        if libc::connect(
+            fd.as_raw_fd(),
+            &addr as *const _ as *const libc::sockaddr,
+            std::mem::size_of::<libc::sockaddr_in>() as libc::socklen_t,
+        ) != 0
+        {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(TcpStream::from(fd))
+    }
+}
+
+/// Exit once `pid` is gone, so the relay does not keep a stopped container's
+/// namespace alive.
+fn watch_pid(pid: u32) {
+    let path = format!("/proc/{pid}");
+    while std::path::Path::new(&path).exists() {
+        thread::sleep(WATCH_INTERVAL);
+    }
+    eprintln!("oqto-egress-relay: pid {pid} is gone, exiting");
+    std::process::exit(0);
+}
+
 /// Bidirectionally copy between two streams until both directions close.
 fn splice(a: TcpStream, b: TcpStream) {
     let (mut a_rd, mut b_wr) = (a.try_clone(), b.try_clone());
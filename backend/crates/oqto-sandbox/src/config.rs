@@ -52,6 +52,8 @@ use std::{ffi::CString, os::unix::ffi::OsStrExt};
 #[allow(unused_imports)]
 use std::io::Write;
 
+use crate::egress_policy::EgressPolicy;
+
 // ============================================================================
 // Guard (FUSE) Configuration
 // ============================================================================
@@ -165,6 +167,16 @@ pub struct NetworkConfig {
     /// Used to configure eavs filtering rules.
     pub allow_domains: Vec<String>,
 
+    /// Domains the agent may not connect to ("example.com" or
+    /// "*.example.com"). Enforced per connection by the egress relay.
+    pub deny_domains: Vec<String>,
+
+    /// Destination ports the agent may connect to. Empty allows all.
+    pub allow_ports: Vec<u16>,
+
+    /// Destination ports the agent may not connect to.
+    pub deny_ports: Vec<u16>,
+
     /// Host-side port of the transparent TCP proxy that captures agent egress
     /// when mode is "proxy". Required for proxy mode (fail-closed if absent).
     #[serde(default)]
@@ -180,6 +192,48 @@ pub struct NetworkConfig {
     pub log_requests: bool,
 }
 
+// ============================================================================
+// Resource Limits (cgroup v2)
+// ============================================================================
+
+/// Memory, CPU and process caps for agent processes, from the `[resources]`
+/// section. `oqto-runner` enforces them with cgroup v2 in local mode, on the
+/// user's agents together and on each session. Unset caps are not enforced.
+#[derive(Debug, Clone, Serialize, Deserialize, Default, PartialEq)]
+#[serde(default)]
+pub struct ResourceConfig {
+    /// Memory limit in MiB, swap included (`memory.max`).
+    pub memory_mb: Option<u64>,
+
+    /// CPU time in cores, e.g. `1.5` (`cpu.max`).
+    pub cpus: Option<f64>,
+
+    /// Maximum number of processes (`pids.max`).
+    pub pids_limit: Option<u64>,
+}
+
+impl ResourceConfig {
+    /// Whether no cap is set.
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// The tighter of both caps for each resource.
+    pub fn tighter(&self, other: &Self) -> Self {
+        fn min<T: PartialOrd>(a: Option<T>, b: Option<T>) -> Option<T> {
+            match (a, b) {
+                (Some(a), Some(b)) => Some(if b < a { b } else { a }),
+                (a, b) => a.or(b),
+            }
+        }
+        Self {
+            memory_mb: min(self.memory_mb, other.memory_mb),
+            cpus: min(self.cpus, other.cpus),
+            pids_limit: min(self.pids_limit, other.pids_limit),
+        }
+    }
+}
+
 // ============================================================================
 // Prompt Configuration
 // ============================================================================
@@ -255,7 +309,8 @@ fn network_restrictiveness(mode: &NetworkMode) -> u8 {
 
 /// Merge two network policies so a workspace override can only tighten, never
 /// loosen, the global policy. On equal restrictiveness the workspace wins (it
-/// may, e.g., point proxy mode at its own ports).
+/// may, e.g., point proxy mode at its own ports). Allow and deny rules are
+/// combined so that neither side's rules are loosened.
 fn merge_network(
     global: &Option<NetworkConfig>,
     workspace: &Option<NetworkConfig>,
@@ -265,11 +320,18 @@ fn merge_network(
         (Some(g), None) => Some(g.clone()),
         (None, Some(w)) => Some(w.clone()),
         (Some(g), Some(w)) => {
-            if network_restrictiveness(&w.mode) >= network_restrictiveness(&g.mode) {
-                Some(w.clone())
+            let mut merged = if network_restrictiveness(&w.mode) >= network_restrictiveness(&g.mode)
+            {
+                w.clone()
             } else {
-                Some(g.clone())
-            }
+                g.clone()
+            };
+            let policy = EgressPolicy::from_network(g).tighter(&EgressPolicy::from_network(w));
+            merged.allow_domains = policy.allow_domains;
+            merged.deny_domains = policy.deny_domains;
+            merged.allow_ports = policy.allow_ports;
+            merged.deny_ports = policy.deny_ports;
+            Some(merged)
         }
     }
 }
@@ -640,10 +702,7 @@ impl SandboxProfile {
             }),
             network: Some(NetworkConfig {
                 mode: NetworkMode::Open,
-                allow_domains: vec![],
-                proxy_tcp_port: None,
-                proxy_dns_port: None,
-                log_requests: false,
+                ..Default::default()
             }),
             prompts: Some(PromptConfig {
                 desktop_notifications: true,
@@ -696,21 +755,55 @@ impl SandboxProfile {
             }),
             network: Some(NetworkConfig {
                 mode: NetworkMode::Isolated,
-                allow_domains: vec![],
-                proxy_tcp_port: None,
-                proxy_dns_port: None,
-                log_requests: false,
+                ..Default::default()
             }),
             prompts: None,
         }
     }
 
+    /// Create a net-off profile: development without network access.
+    pub fn net_off() -> Self {
+        Self {
+            isolate_network: true,
+            ssh: Some(SshProxyConfig {
+                enabled: false,
+                ..Default::default()
+            }),
+            network: Some(NetworkConfig {
+                mode: NetworkMode::Isolated,
+                ..Default::default()
+            }),
+            ..Self::development()
+        }
+    }
+
+    /// Create a read-only-home profile: development with the home directory
+    /// read-only except for agent session state.
+    pub fn read_only_home() -> Self {
+        Self {
+            allow_write: vec![
+                "~/.pi".to_string(),
+                "~/.claude".to_string(),
+                "~/.local/share/claude".to_string(),
+                "~/.cache/claude".to_string(),
+                "~/.codex".to_string(),
+                "~/.local/share/codex".to_string(),
+                "~/.cache/codex".to_string(),
+                "/tmp".to_string(),
+            ],
+            overlay_paths: vec![],
+            ..Self::development()
+        }
+    }
+
     /// Get a built-in profile by name.
     pub fn builtin(name: &str) -> Option<Self> {
         match name {
             "minimal" => Some(Self::minimal()),
             "development" => Some(Self::development()),
             "strict" => Some(Self::strict()),
+            "net-off" => Some(Self::net_off()),
+            "read-only-home" => Some(Self::read_only_home()),
             _ => None,
         }
     }
@@ -746,6 +839,10 @@ pub struct SandboxConfigFile {
     /// Keys are profile names, values are profile settings.
     #[serde(default)]
     pub profiles: HashMap<String, SandboxProfile>,
+
+    /// Memory, CPU and process caps for agent processes.
+    #[serde(default)]
+    pub resources: ResourceConfig,
 }
 
 /// Sandbox configuration (resolved).
@@ -820,9 +917,27 @@ pub struct SandboxConfig {
     #[serde(default)]
     pub network: Option<NetworkConfig>,
 
+    /// Memory, CPU and process caps for agent processes (cgroup v2).
+    #[serde(default)]
+    pub resources: ResourceConfig,
+
     /// Custom profiles loaded from config (for workspace merging).
     #[serde(skip_serializing_if = "HashMap::is_empty", default)]
     pub profiles: HashMap<String, SandboxProfile>,
+
+    /// Profile a session selected, nested inside `profile` (see
+    /// [`SandboxConfig::with_session_profile`]).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub session_profile: Option<String>,
+}
+
+/// Session profile that keeps the runner's sandbox as configured.
+pub const FULL_SESSION_PROFILE: &str = "full";
+
+/// Whether a profile binds the home directory read-write. The others bind it
+/// read-only with only `allow_write` writable on top.
+fn profile_home_writable(name: &str) -> bool {
+    matches!(name, "development" | "minimal" | "net-off")
 }
 
 /// Path to user-level sandbox config file (for single-user mode).
@@ -855,7 +970,9 @@ impl Default for SandboxConfig {
             overlay_paths: profile.overlay_paths,
             scoped_paths: profile.scoped_paths,
             network: profile.network,
+            resources: ResourceConfig::default(),
             profiles: HashMap::new(),
+            session_profile: None,
         }
     }
 }
@@ -904,7 +1021,9 @@ impl From<SandboxConfigFile> for SandboxConfig {
             overlay_paths: profile.overlay_paths,
             scoped_paths: profile.scoped_paths,
             network: profile.network,
+            resources: file.resources,
             profiles: file.profiles,
+            session_profile: None,
         };
 
         // Always ensure sandbox.toml itself is protected
@@ -943,7 +1062,9 @@ impl SandboxConfig {
             overlay_paths: profile.overlay_paths,
             scoped_paths: profile.scoped_paths,
             network: profile.network,
+            resources: ResourceConfig::default(),
             profiles: HashMap::new(),
+            session_profile: None,
         }
     }
 
@@ -972,7 +1093,9 @@ impl SandboxConfig {
             overlay_paths: profile.overlay_paths,
             scoped_paths: profile.scoped_paths,
             network: profile.network,
+            resources: ResourceConfig::default(),
             profiles: HashMap::new(),
+            session_profile: None,
         }
     }
 
@@ -1022,7 +1145,9 @@ impl SandboxConfig {
             overlay_paths: profile.overlay_paths,
             scoped_paths: profile.scoped_paths,
             network: profile.network,
+            resources: ResourceConfig::default(),
             profiles: custom_profiles.clone(),
+            session_profile: None,
         };
 
         // Always ensure sandbox.toml itself is protected
@@ -1136,7 +1261,9 @@ impl SandboxConfig {
                         overlay_paths: profile.overlay_paths,
                         scoped_paths: profile.scoped_paths,
                         network: profile.network,
+                        resources: file.resources,
                         profiles: merged_profiles,
+                        session_profile: None,
                     };
 
                     info!(
@@ -1257,7 +1384,13 @@ impl SandboxConfig {
             scoped_paths,
             // Network policy: workspace may only tighten (Open < Proxy < Isolated).
             network: merge_network(&self.network, &workspace_config.network),
+            // Resource caps: workspace may only tighten.
+            resources: self.resources.tighter(&workspace_config.resources),
             profiles,
+            session_profile: workspace_config
+                .session_profile
+                .clone()
+                .or_else(|| self.session_profile.clone()),
         }
     }
 
@@ -1291,6 +1424,31 @@ impl SandboxConfig {
         }
     }
 
+    /// Nest the profile a session selected inside this config.
+    ///
+    /// The profile (custom from `[profiles.<name>]`, else built-in) is merged
+    /// like a workspace config, so it can only add restrictions; "full" and
+    /// the configured profile keep this config as it is. A session that
+    /// selects another profile is sandboxed even when sandboxing is disabled.
+    pub fn with_session_profile(&self, name: &str) -> Result<Self> {
+        if name == FULL_SESSION_PROFILE || name == self.profile {
+            return Ok(self.clone());
+        }
+        if !self.profiles.contains_key(name) && SandboxProfile::builtin(name).is_none() {
+            anyhow::bail!("Unknown sandbox profile '{name}'");
+        }
+        let session = Self::from_profile_with_custom(name, &self.profiles);
+        let mut merged = self.merge_with_workspace(&session);
+        merged.profile = self.profile.clone();
+        merged.session_profile = Some(name.to_string());
+        Ok(merged)
+    }
+
+    /// Name of the profile in effect: the session's, else the configured one.
+    pub fn active_profile(&self) -> &str {
+        self.session_profile.as_deref().unwrap_or(&self.profile)
+    }
+
     fn apply_scoped_path_rules(
         &self,
         args: &mut Vec<String>,
@@ -1534,7 +1692,11 @@ impl SandboxConfig {
         //
         // The development approach is more permissive but simpler - agents can write anywhere
         // in home except explicitly denied paths. oqto-guard provides additional runtime control.
-        let home_writable = self.profile == "development" || self.profile == "minimal";
+        let home_writable = profile_home_writable(&self.profile)
+            && self
+                .session_profile
+                .as_deref()
+                .is_none_or(profile_home_writable);
 
         if let Some(ref home) = target_home {
             let home_str = home.to_string_lossy().to_string();
@@ -2381,6 +2543,36 @@ mod tests {
         assert!(!unknown.isolate_network); // development has isolate_network=false
     }
 
+    #[test]
+    fn test_session_profiles_only_tighten() {
+        let mut global = SandboxConfig::from_profile("development");
+        global.enabled = false;
+
+        let full = global.with_session_profile("full").unwrap();
+        assert!(!full.enabled);
+        assert_eq!(full.active_profile(), "development");
+
+        let net_off = global.with_session_profile("net-off").unwrap();
+        assert!(net_off.enabled);
+        assert!(net_off.isolate_network);
+        assert_eq!(net_off.network_mode(), NetworkMode::Isolated);
+        assert_eq!(net_off.profile, "development");
+        assert_eq!(net_off.active_profile(), "net-off");
+
+        let read_only = global.with_session_profile("read-only-home").unwrap();
+        assert!(!read_only.allow_write.contains(&"~/.cargo".to_string()));
+        assert!(read_only.allow_write.contains(&"~/.pi".to_string()));
+
+        // A session on a strict runner cannot get the network back.
+        let strict = SandboxConfig::from_profile("strict")
+            .with_session_profile("minimal")
+            .unwrap();
+        assert!(strict.isolate_network);
+        assert!(strict.deny_read.contains(&"~/.config".to_string()));
+
+        assert!(global.with_session_profile("nonexistent").is_err());
+    }
+
     #[test]
     fn test_custom_profile_parsing() {
         let toml_content = r#"
@@ -2407,6 +2599,41 @@ isolate_pid = false
         assert!(!config.isolate_pid);
     }
 
+    #[test]
+    fn test_resource_limits() {
+        let toml_content = r#"
+enabled = true
+
+[resources]
+memory_mb = 4096
+cpus = 2.0
+"#;
+
+        let file: SandboxConfigFile = toml::from_str(toml_content).unwrap();
+        let global: SandboxConfig = file.into();
+        assert_eq!(global.resources.memory_mb, Some(4096));
+        assert_eq!(global.resources.cpus, Some(2.0));
+        assert_eq!(global.resources.pids_limit, None);
+
+        // A workspace can only tighten the caps.
+        let mut workspace = SandboxConfig::default();
+        workspace.resources = ResourceConfig {
+            memory_mb: Some(8192),
+            cpus: Some(0.5),
+            pids_limit: Some(256),
+        };
+        let merged = global.merge_with_workspace(&workspace);
+        assert_eq!(
+            merged.resources,
+            ResourceConfig {
+                memory_mb: Some(4096),
+                cpus: Some(0.5),
+                pids_limit: Some(256),
+            }
+        );
+        assert!(SandboxConfig::default().resources.is_empty());
+    }
+
     #[test]
     fn test_custom_profile_with_builtin_reference() {
         // Workspace can reference a built-in profile
@@ -2932,5 +3159,25 @@ log_requests = true
         );
         // None on both sides stays None.
         assert!(merge_network(&None, &None).is_none());
+
+        // Rules of both sides are kept.
+        let global = Some(NetworkConfig {
+            mode: NetworkMode::Proxy,
+            proxy_tcp_port: Some(8443),
+            allow_domains: vec!["*.github.com".into()],
+            deny_ports: vec![25],
+            ..Default::default()
+        });
+        let workspace = Some(NetworkConfig {
+            mode: NetworkMode::Open,
+            allow_domains: vec!["api.github.com".into(), "pypi.org".into()],
+            deny_domains: vec!["gist.github.com".into()],
+            ..Default::default()
+        });
+        let merged = merge_network(&global, &workspace).unwrap();
+        assert_eq!(merged.mode, NetworkMode::Proxy);
+        assert_eq!(merged.allow_domains, vec!["api.github.com".to_string()]);
+        assert_eq!(merged.deny_domains, vec!["gist.github.com".to_string()]);
+        assert_eq!(merged.deny_ports, vec![25]);
     }
 }
@@ -22,6 +22,10 @@
 //!   (it computes layout and emits `ip`/`nft` argv + ruleset text), so it is
 //!   exhaustively unit-testable without privileges;
 //! - `apply`/`teardown` execute those commands and require `CAP_NET_ADMIN`.
+//!
+//! Containers bring their own network namespace. [`attach_container`] puts a
+//! relay into it instead and redirects the container's TCP to that relay, which
+//! enforces the [`EgressPolicy`] and connects to the original destination.
 
 use std::ffi::CString;
 use std::fs::{self, OpenOptions};
@@ -29,12 +33,13 @@ use std::io::Write;
 use std::net::Ipv4Addr;
 use std::os::unix::io::AsRawFd;
 use std::os::unix::process::CommandExt;
-use std::process::{Child, Command, Stdio};
+use std::process::{Child, ChildStdout, Command, Stdio};
 
 use anyhow::{Context, Result, bail};
 use log::{debug, info, warn};
 
 use crate::config::{NetworkConfig, NetworkMode};
+use crate::egress_policy::{EgressPolicy, RELAY_POLICY_ENV};
 
 /// Directory where `iproute2` exposes named network namespaces.
 const NETNS_DIR: &str = "/var/run/netns";
@@ -59,6 +64,13 @@ const PREFIX: u8 = 30;
 /// isolated, so it only needs to be unique within the namespace.
 const RELAY_PORT: u16 = 10000;
 
+/// Port the relay listens on (loopback) inside a container's namespace, away
+/// from the ports agents commonly serve on.
+const CONTAINER_RELAY_PORT: u16 = 15001;
+
+/// Name of the nftables table owning all egress rules in a namespace.
+const NFT_TABLE: &str = "oqto_egress";
+
 /// Where the transparent proxy and DNS resolver listen on the host side of the
 /// veth. Ports are host-configured; the bind address is the allocated host
 /// veth IP (the agent never learns the real upstream).
@@ -94,6 +106,8 @@ pub struct EgressPlan {
     /// domains the proxy is expected to allow (passed through for the proxy's
     /// own ACL; not enforced by nftables, which only does capture)
     pub allow_domains: Vec<String>,
+    /// allow/deny rules the relay enforces per connection
+    pub policy: EgressPolicy,
 }
 
 /// Compute the `(host_ip, guest_ip)` pair for a `/30` block index.
@@ -124,6 +138,10 @@ impl EgressPlan {
             guest_ip,
             prefix: PREFIX,
             proxy,
+            policy: EgressPolicy {
+                allow_domains: allow_domains.clone(),
+                ..Default::default()
+            },
             allow_domains,
         })
     }
@@ -142,11 +160,12 @@ impl EgressPlan {
                      (fail-closed)",
                 )?;
                 let dns_port = cfg.proxy_dns_port.unwrap_or(tcp_port);
-                let plan = Self::new(
+                let mut plan = Self::new(
                     index,
                     EgressProxy { tcp_port, dns_port },
                     cfg.allow_domains.clone(),
                 )?;
+                plan.policy = EgressPolicy::from_network(cfg);
                 Ok(Some(plan))
             }
         }
@@ -384,15 +403,14 @@ pub struct EgressGuard {
     /// The in-namespace `oqto-egress-relay` process, killed on drop before the
     /// namespace is torn down.
     relay: Option<Child>,
+    /// Container namespace the rules were attached to, see [`attach_container`].
+    attached: Option<String>,
 }
 
 impl EgressGuard {
     /// Inert guard: no namespace, Drop is a no-op.
     pub fn inert() -> Self {
-        Self {
-            plan: None,
-            relay: None,
-        }
+        Self::default()
     }
 
     /// The applied plan, if proxy mode created a namespace. Pass this to
@@ -400,6 +418,13 @@ impl EgressGuard {
     pub fn plan(&self) -> Option<&EgressPlan> {
         self.plan.as_ref()
     }
+
+    /// The relay's report of blocked connections: one JSON
+    /// [`BlockedConnection`](crate::BlockedConnection) per line. None for an
+    /// inert guard or once taken. Reports are dropped while nobody reads.
+    pub fn take_blocked(&mut self) -> Option<ChildStdout> {
+        self.relay.as_mut()?.stdout.take()
+    }
 }
 
 impl Drop for EgressGuard {
@@ -412,6 +437,15 @@ impl Drop for EgressGuard {
         if let Some(plan) = &self.plan {
             plan.teardown();
         }
+        if let Some(netns) = &self.attached {
+            // The namespace is usually gone with its container by now.
+            if let Err(e) = run(
+                &nsenter_nft(netns, &["delete", "table", "inet", NFT_TABLE]),
+                None,
+            ) {
+                debug!("egress: removing rules from {netns} failed (ignored): {e:#}");
+            }
+        }
     }
 }
 
@@ -459,31 +493,186 @@ pub fn prepare(cfg: Option<&NetworkConfig>) -> Result<EgressGuard> {
     Ok(EgressGuard {
         plan: Some(plan),
         relay: Some(relay),
+        attached: None,
     })
 }
 
+/// Enforce `policy` on the egress of a running container, given the host pid
+/// of a process in it.
+///
+/// Starts a relay in the container's network namespace and adds nftables rules
+/// there that redirect its outgoing IPv4 TCP to the relay, apply the port rules
+/// to UDP (DNS stays allowed) and drop IPv6 egress. The relay checks each
+/// connection and opens it to the original destination itself; its own
+/// connections carry [`RELAY_MARK`](crate::egress_relay::RELAY_MARK) and pass
+/// the rules. It exits when `pid` does. Requires root; fails closed.
+pub fn attach_container(pid: u32, policy: &EgressPolicy) -> Result<EgressGuard> {
+    if !privileged() {
+        bail!("egress rules for containers require root; refusing to start (fail-closed)");
+    }
+    let netns = format!("/proc/{pid}/ns/net");
+    let listen = std::net::SocketAddrV4::new(Ipv4Addr::LOCALHOST, CONTAINER_RELAY_PORT);
+    let relay = spawn_relay_in(&netns, listen.into(), None, Some(pid), policy)?;
+    let mut guard = EgressGuard {
+        plan: None,
+        relay: Some(relay),
+        attached: None,
+    };
+    run(
+        &nsenter_nft(&netns, &["-f", "-"]),
+        Some(container_nft_ruleset(policy).as_bytes()),
+    )
+    .with_context(|| format!("applying egress rules to the namespace of pid {pid}"))?;
+    guard.attached = Some(netns);
+    info!("egress: rules attached to the namespace of pid {pid}");
+    Ok(guard)
+}
+
+/// Cut a container off the network, given the host pid of a process in it.
+///
+/// Adds nftables rules to the container's network namespace that drop every
+/// outgoing packet except on loopback and replies to connections made to it,
+/// so its published ports keep working. Requires root; fails closed.
+pub fn isolate_container(pid: u32) -> Result<EgressGuard> {
+    if !privileged() {
+        bail!("network isolation for containers requires root; refusing to start (fail-closed)");
+    }
+    let netns = format!("/proc/{pid}/ns/net");
+    run(
+        &nsenter_nft(&netns, &["-f", "-"]),
+        Some(container_isolation_ruleset().as_bytes()),
+    )
+    .with_context(|| format!("isolating the namespace of pid {pid}"))?;
+    info!("egress: namespace of pid {pid} isolated");
+    Ok(EgressGuard {
+        plan: None,
+        relay: None,
+        attached: Some(netns),
+    })
+}
+
+/// The nftables ruleset for an isolated container namespace, see
+/// [`isolate_container`].
+fn container_isolation_ruleset() -> String {
+    format!(
+        "table inet {NFT_TABLE} {{\n\
+         \x20   chain output_filter {{\n\
+         \x20       type filter hook output priority 0; policy drop;\n\
+         \x20       oif \"lo\" accept\n\
+         \x20       ct state established,related accept\n\
+         \x20   }}\n\
+         }}\n"
+    )
+}
+
+/// Argv running `nft` with `args` in the network namespace at `netns`.
+fn nsenter_nft(netns: &str, args: &[&str]) -> Vec<String> {
+    let mut argv = vec![
+        "nsenter".to_string(),
+        format!("--net={netns}"),
+        "nft".to_string(),
+    ];
+    argv.extend(args.iter().map(|a| a.to_string()));
+    argv
+}
+
+/// The nftables ruleset for a container namespace, see [`attach_container`].
+fn container_nft_ruleset(policy: &EgressPolicy) -> String {
+    let mark = crate::egress_relay::RELAY_MARK;
+    let relay = CONTAINER_RELAY_PORT;
+    let set = |ports: &[u16]| {
+        ports
+            .iter()
+            .map(u16::to_string)
+            .collect::<Vec<_>>()
+            .join(", ")
+    };
+    let mut udp_rules = String::new();
+    if !policy.deny_ports.is_empty() {
+        udp_rules.push_str(&format!(
+            "\x20       udp dport {{ {} }} drop\n",
+            set(&policy.deny_ports)
+        ));
+    }
+    if !policy.allow_ports.is_empty() {
+        udp_rules.push_str(&format!(
+            "\x20       udp dport != {{ {} }} drop\n",
+            set(&policy.allow_ports)
+        ));
+    }
+    format!(
+        "table inet {NFT_TABLE} {{\n\
+         \x20   chain output_nat {{\n\
+         \x20       type nat hook output priority -100; policy accept;\n\
+         \x20       meta mark {mark} accept\n\
+         \x20       ip daddr 127.0.0.0/8 accept\n\
+         \x20       meta nfproto ipv4 meta l4proto tcp redirect to :{relay}\n\
+         \x20   }}\n\
+         \x20   chain output_filter {{\n\
+         \x20       type filter hook output priority 0; policy accept;\n\
+         \x20       oif \"lo\" accept\n\
+         \x20       meta mark {mark} accept\n\
+         \x20       meta nfproto ipv6 drop\n\
+         \x20       udp dport 53 accept\n\
+         {udp_rules}\
+         \x20   }}\n\
+         }}\n"
+    )
+}
+
 /// Launch `oqto-egress-relay` inside the plan's namespace. The relay joins the
 /// namespace via `setns` in a pre-exec hook (so it sees the agent's DNAT) and
 /// is told its listen address and the eavs endpoint via env. Requires the relay
 /// binary to be resolvable; fails closed otherwise.
 fn spawn_relay(plan: &EgressPlan) -> Result<Child> {
+    let child = spawn_relay_in(
+        &plan.netns_path(),
+        plan.relay_listen().into(),
+        Some(plan.eavs_endpoint()),
+        None,
+        &plan.policy,
+    )?;
+    info!(
+        "egress: relay started (pid {:?}) listening {} -> eavs {}",
+        child.id(),
+        plan.relay_listen(),
+        plan.eavs_endpoint()
+    );
+    Ok(child)
+}
+
+/// Launch `oqto-egress-relay` in the network namespace at `netns_path`. Without
+/// `eavs` it connects to the original destinations itself; with `watch_pid` it
+/// exits when that process does.
+fn spawn_relay_in(
+    netns_path: &str,
+    listen: std::net::SocketAddr,
+    eavs: Option<std::net::SocketAddrV4>,
+    watch_pid: Option<u32>,
+    policy: &EgressPolicy,
+) -> Result<Child> {
     let bin = crate::egress_relay::resolve_relay_binary().context(
         "oqto-egress-relay binary not found (set OQTO_EGRESS_RELAY_BIN or install it on PATH); \
-         refusing proxy mode (fail-closed)",
+         refusing egress control (fail-closed)",
     )?;
-    let netns_path = CString::new(plan.netns_path())
-        .map_err(|_| anyhow::anyhow!("netns path contains NUL byte"))?;
+    let netns_path =
+        CString::new(netns_path).map_err(|_| anyhow::anyhow!("netns path contains NUL byte"))?;
 
     let mut cmd = Command::new(&bin);
-    cmd.env(
-        crate::egress_relay::RELAY_LISTEN_ENV,
-        plan.relay_listen().to_string(),
-    );
-    cmd.env(
-        crate::egress_relay::RELAY_EAVS_ENV,
-        plan.eavs_endpoint().to_string(),
-    );
-    cmd.stdin(Stdio::null());
+    cmd.env(crate::egress_relay::RELAY_LISTEN_ENV, listen.to_string());
+    if let Some(eavs) = eavs {
+        cmd.env(crate::egress_relay::RELAY_EAVS_ENV, eavs.to_string());
+    }
+    if let Some(pid) = watch_pid {
+        cmd.env(crate::egress_relay::RELAY_WATCH_PID_ENV, pid.to_string());
+    }
+    if !policy.is_empty() {
+        cmd.env(
+            RELAY_POLICY_ENV,
+            serde_json::to_string(policy).context("serializing egress policy")?,
+        );
+    }
+    cmd.stdin(Stdio::null()).stdout(Stdio::piped());
 
     // SAFETY: pre_exec runs in the child after fork, before exec; it only makes
     // async-signal-safe syscalls on a pre-built CString.
@@ -503,16 +692,8 @@ fn spawn_relay(plan: &EgressPlan) -> Result<Child> {
         });
     }
 
-    let child = cmd
-        .spawn()
-        .with_context(|| format!("spawning oqto-egress-relay from {}", bin.display()))?;
-    info!(
-        "egress: relay started (pid {:?}) listening {} -> eavs {}",
-        child.id(),
-        plan.relay_listen(),
-        plan.eavs_endpoint()
-    );
-    Ok(child)
+    cmd.spawn()
+        .with_context(|| format!("spawning oqto-egress-relay from {}", bin.display()))
 }
 
 /// Lowest `/30` block index not currently backed by a live `oqto-egr-*`
@@ -709,6 +890,41 @@ mod tests {
         assert!(!rs.contains("snat"), "must not snat:\n{rs}");
     }
 
+    #[test]
+    fn container_ruleset_redirects_tcp_and_applies_udp_port_rules() {
+        let policy = EgressPolicy {
+            allow_ports: vec![443, 80],
+            deny_ports: vec![25],
+            ..Default::default()
+        };
+        let rs = container_nft_ruleset(&policy);
+        let mark = crate::egress_relay::RELAY_MARK;
+        assert!(rs.contains("meta nfproto ipv4 meta l4proto tcp redirect to :15001"));
+        // The relay's own connections must not loop back into it.
+        assert!(rs.contains(&format!("meta mark {mark} accept")));
+        assert!(rs.contains("udp dport 53 accept"));
+        assert!(rs.contains("udp dport { 25 } drop"));
+        assert!(rs.contains("udp dport != { 443, 80 } drop"));
+        assert!(rs.contains("meta nfproto ipv6 drop"));
+
+        let open = container_nft_ruleset(&EgressPolicy::default());
+        assert!(!open.contains("udp dport !="), "{open}");
+        assert_eq!(
+            nsenter_nft("/proc/42/ns/net", &["-f", "-"]),
+            vec!["nsenter", "--net=/proc/42/ns/net", "nft", "-f", "-"]
+        );
+    }
+
+    #[test]
+    fn container_isolation_drops_all_but_loopback_and_replies() {
+        let rs = container_isolation_ruleset();
+        assert!(rs.contains("type filter hook output priority 0; policy drop;"));
+        assert!(rs.contains("oif \"lo\" accept"));
+        // Published ports answer through established connections.
+        assert!(rs.contains("ct state established,related accept"));
+        assert!(!rs.contains("redirect"), "{rs}");
+    }
+
     #[test]
     fn relay_and_eavs_endpoints_derive_from_subnet() {
         // index 0 -> host 10.0.0.1, guest 10.0.0.2; proxy() tcp_port = 8443.
@@ -0,0 +1,347 @@
+//! Allow/deny rules for agent egress, enforced per connection by
+//! `oqto-egress-relay`.
+//!
+//! The relay sees the original destination address and port of each TCP
+//! connection. Domains are taken from the first bytes the agent sends: the
+//! SNI of a TLS ClientHello or the `Host` header of an HTTP request. When
+//! domains are allowlisted, connections whose host cannot be read are
+//! blocked.
+//!
+//! Blocked connections are written to the relay's stdout as one JSON
+//! [`BlockedConnection`] per line, so the spawner can report them.
+
+use serde::{Deserialize, Serialize};
+
+use crate::config::NetworkConfig;
+
+/// Env var carrying the [`EgressPolicy`] as JSON.
+pub const RELAY_POLICY_ENV: &str = "OQTO_EGRESS_RELAY_POLICY";
+
+/// Most bytes read ahead of a connection to find its host.
+pub const MAX_SNIFF_BYTES: usize = 16 * 1024;
+
+/// Domains and ports an agent may connect to.
+///
+/// Domains match exactly, or with a `*.` prefix any subdomain. Empty allow
+/// lists allow everything that is not denied.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct EgressPolicy {
+    pub allow_domains: Vec<String>,
+    pub deny_domains: Vec<String>,
+    pub allow_ports: Vec<u16>,
+    pub deny_ports: Vec<u16>,
+}
+
+/// A connection the relay refused.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BlockedConnection {
+    /// Host the agent asked for, when it could be read.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub host: Option<String>,
+    /// Destination IP address.
+    pub address: String,
+    pub port: u16,
+    pub reason: String,
+}
+
+impl EgressPolicy {
+    /// The rules of a network config.
+    pub fn from_network(config: &NetworkConfig) -> Self {
+        Self {
+            allow_domains: config.allow_domains.clone(),
+            deny_domains: config.deny_domains.clone(),
+            allow_ports: config.allow_ports.clone(),
+            deny_ports: config.deny_ports.clone(),
+        }
+    }
+
+    /// Whether the policy has no rules.
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// Whether checks need the host of a connection.
+    pub fn needs_host(&self) -> bool {
+        !self.allow_domains.is_empty() || !self.deny_domains.is_empty()
+    }
+
+    /// Check a connection to `port`, for `host` if it could be read.
+    /// Returns why it is blocked.
+    pub fn check(&self, host: Option<&str>, port: u16) -> Result<(), String> {
+        if self.deny_ports.contains(&port) {
+            return Err(format!("port {port} is denied"));
+        }
+        if !self.allow_ports.is_empty() && !self.allow_ports.contains(&port) {
+            return Err(format!("port {port} is not allowed"));
+        }
+        match host {
+            Some(host) => {
+                if domain_listed(&self.deny_domains, host) {
+                    return Err(format!("{host} is denied"));
+                }
+                if !self.allow_domains.is_empty() && !domain_listed(&self.allow_domains, host) {
+                    return Err(format!("{host} is not allowed"));
+                }
+            }
+            None if !self.allow_domains.is_empty() => {
+                return Err("host unknown (only TLS and HTTP can be checked)".to_string());
+            }
+            None => {}
+        }
+        Ok(())
+    }
+
+    /// A policy at least as strict as both: denials are combined, allow
+    /// lists keep only entries both permit.
+    pub fn tighter(&self, other: &Self) -> Self {
+        fn union<T: Clone + PartialEq>(a: &[T], b: &[T]) -> Vec<T> {
+            let mut out = a.to_vec();
+            out.extend(b.iter().filter(|v| !a.contains(v)).cloned());
+            out
+        }
+        let allow_domains = match (
+            self.allow_domains.is_empty(),
+            other.allow_domains.is_empty(),
+        ) {
+            (true, _) => other.allow_domains.clone(),
+            (_, true) => self.allow_domains.clone(),
+            _ => union(
+                &covered(&other.allow_domains, &self.allow_domains),
+                &covered(&self.allow_domains, &other.allow_domains),
+            ),
+        };
+        let allow_ports = match (self.allow_ports.is_empty(), other.allow_ports.is_empty()) {
+            (true, _) => other.allow_ports.clone(),
+            (_, true) => self.allow_ports.clone(),
+            _ => self
+                .allow_ports
+                .iter()
+                .filter(|p| other.allow_ports.contains(p))
+                .copied()
+                .collect(),
+        };
+        Self {
+            allow_domains,
+            deny_domains: union(&self.deny_domains, &other.deny_domains),
+            allow_ports,
+            deny_ports: union(&self.deny_ports, &other.deny_ports),
+        }
+    }
+}
+
+/// Entries of `domains` that `by` permits. A `*.` entry is kept when `by`
+/// permits the same or a wider wildcard.
+fn covered(domains: &[String], by: &[String]) -> Vec<String> {
+    domains
+        .iter()
+        .filter(|d| match d.strip_prefix("*.") {
+            Some(base) => by.iter().any(|b| {
+                b.strip_prefix("*.") == Some(base)
+                    || (b.starts_with("*.") && domain_matches(b, base))
+            }),
+            None => domain_listed(by, d),
+        })
+        .cloned()
+        .collect()
+}
+
+fn domain_listed(list: &[String], host: &str) -> bool {
+    list.iter().any(|pattern| domain_matches(pattern, host))
+}
+
+/// Whether `host` matches `pattern` ("example.com" or "*.example.com").
+pub fn domain_matches(pattern: &str, host: &str) -> bool {
+    let pattern = pattern.trim().trim_end_matches('.').to_ascii_lowercase();
+    let host = host.trim().trim_end_matches('.').to_ascii_lowercase();
+    match pattern.strip_prefix("*.") {
+        Some(base) => host
+            .strip_suffix(base)
+            .is_some_and(|sub| sub.len() > 1 && sub.ends_with('.')),
+        None => host == pattern,
+    }
+}
+
+/// The host a connection is for, from its first bytes: the SNI of a TLS
+/// ClientHello or the `Host` header of an HTTP/1 request. None when it
+/// cannot be read (yet).
+pub fn sniff_host(data: &[u8]) -> Option<String> {
+    let host = if data.first() == Some(&0x16) {
+        tls_server_name(data)?
+    } else {
+        http_host(data)?
+    };
+    let host = host.trim().trim_end_matches('.').to_ascii_lowercase();
+    (!host.is_empty()).then_some(host)
+}
+
+/// Server name of a TLS ClientHello in the first record.
+fn tls_server_name(data: &[u8]) -> Option<String> {
+    let mut r = Reader(data);
+    // Record header: type, version, length; then the handshake header.
+    r.skip(3)?;
+    let len = r.u16()? as usize;
+    let record = r.take(len)?;
+    let mut r = Reader(record);
+    if r.u8()? != 1 {
+        return None;
+    }
+    r.skip(3)?; // handshake length
+    r.skip(2 + 32)?; // client version, random
+    let session_id = r.u8()? as usize;
+    r.skip(session_id)?;
+    let suites = r.u16()? as usize;
+    r.skip(suites)?;
+    let compression = r.u8()? as usize;
+    r.skip(compression)?;
+    let len = r.u16()? as usize;
+    let mut extensions = Reader(r.take(len)?);
+    while let Some(kind) = extensions.u16() {
+        let len = extensions.u16()? as usize;
+        let body = extensions.take(len)?;
+        if kind != 0 {
+            continue;
+        }
+        let mut names = Reader(body);
+        let len = names.u16()? as usize;
+        let mut list = Reader(names.take(len)?);
+        while let Some(name_type) = list.u8() {
+            let len = list.u16()? as usize;
+            let name = list.take(len)?;
+            if name_type == 0 {
+                return String::from_utf8(name.to_vec()).ok();
+            }
+        }
+    }
+    None
+}
+
+/// `Host` header of an HTTP/1 request, without the port.
+fn http_host(data: &[u8]) -> Option<String> {
+    let head_end = data.windows(4).position(|w| w == b"\r\n\r\n")?;
+    let head = std::str::from_utf8(&data[..head_end]).ok()?;
+    let mut lines = head.split("\r\n");
+    let request_line = lines.next()?;
+    if !request_line.ends_with("HTTP/1.1") && !request_line.ends_with("HTTP/1.0") {
+        return None;
+    }
+    let value = lines.find_map(|line| {
+        let (name, value) = line.split_once(':')?;
+        name.trim()
+            .eq_ignore_ascii_case("host")
+            .then_some(value.trim())
+    })?;
+    let host = match value.strip_prefix('[') {
+        Some(v6) => v6.split(']').next()?,
+        None => value.rsplit_once(':').map_or(value, |(host, _)| host),
+    };
+    Some(host.to_string())
+}
+
+/// Big-endian reader that returns None past the end.
+struct Reader<'a>(&'a [u8]);
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, n: usize) -> Option<&'a [u8]> {
+        if self.0.len() < n {
+            return None;
+        }
+        let (head, rest) = self.0.split_at(n);
+        self.0 = rest;
+        Some(head)
+    }
+
+    fn skip(&mut self, n: usize) -> Option<()> {
+        self.take(n).map(|_| ())
+    }
+
+    fn u8(&mut self) -> Option<u8> {
+        self.take(1).map(|b| b[0])
+    }
+
+    fn u16(&mut self) -> Option<u16> {
+        self.take(2).map(|b| u16::from_be_bytes([b[0], b[1]]))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn client_hello(name: &str) -> Vec<u8> {
+        let mut sni = Vec::new();
+        sni.extend_from_slice(&((name.len() + 3) as u16).to_be_bytes());
+        sni.push(0);
+        sni.extend_from_slice(&(name.len() as u16).to_be_bytes());
+        sni.extend_from_slice(name.as_bytes());
+        let mut extensions = vec![0x00, 0x0b, 0x00, 0x02, 0x01, 0x00]; // ec_point_formats
+        extensions.extend_from_slice(&[0x00, 0x00]);
+        extensions.extend_from_slice(&(sni.len() as u16).to_be_bytes());
+        extensions.extend_from_slice(&sni);
+
+        let mut hello = vec![0x03, 0x03];
+        hello.extend_from_slice(&[7; 32]);
+        hello.extend_from_slice(&[0, 0, 2, 0x13, 0x01, 1, 0]);
+        hello.extend_from_slice(&(extensions.len() as u16).to_be_bytes());
+        hello.extend_from_slice(&extensions);
+
+        let mut handshake = vec![1, 0];
+        handshake.extend_from_slice(&(hello.len() as u16).to_be_bytes());
+        handshake.extend_from_slice(&hello);
+        let mut record = vec![0x16, 0x03, 0x01];
+        record.extend_from_slice(&(handshake.len() as u16).to_be_bytes());
+        record.extend_from_slice(&handshake);
+        record
+    }
+
+    #[test]
+    fn hosts_are_read_from_tls_and_http() {
+        assert_eq!(
+            sniff_host(&client_hello("API.GitHub.com")).as_deref(),
+            Some("api.github.com")
+        );
+        let hello = client_hello("example.com");
+        assert_eq!(sniff_host(&hello[..hello.len() - 4]), None);
+        assert_eq!(
+            sniff_host(b"GET / HTTP/1.1\r\nUser-Agent: x\r\nhost: pypi.org:8080\r\n\r\n")
+                .as_deref(),
+            Some("pypi.org")
+        );
+        assert_eq!(sniff_host(b"GET / HTTP/1.1\r\nHost: pypi.org"), None);
+        assert_eq!(sniff_host(b"SSH-2.0-OpenSSH_9.6\r\n"), None);
+    }
+
+    #[test]
+    fn policies_check_and_tighten() {
+        let policy = EgressPolicy {
+            allow_domains: vec!["github.com".into(), "*.anthropic.com".into()],
+            deny_domains: vec!["evil.anthropic.com".into()],
+            allow_ports: vec![443, 80],
+            deny_ports: vec![],
+        };
+        assert!(policy.check(Some("github.com"), 443).is_ok());
+        assert!(policy.check(Some("api.anthropic.com"), 443).is_ok());
+        assert!(policy.check(Some("anthropic.com"), 443).is_err());
+        assert!(policy.check(Some("evil.anthropic.com"), 443).is_err());
+        assert!(policy.check(Some("github.com"), 22).is_err());
+        assert!(policy.check(None, 443).is_err());
+
+        let deny_only = EgressPolicy {
+            deny_ports: vec![25],
+            ..Default::default()
+        };
+        assert!(deny_only.check(None, 443).is_ok());
+        assert!(deny_only.check(None, 25).is_err());
+
+        let narrower = EgressPolicy {
+            allow_domains: vec!["api.anthropic.com".into(), "gitlab.com".into()],
+            allow_ports: vec![443],
+            ..Default::default()
+        };
+        let tight = policy.tighter(&narrower);
+        assert_eq!(tight.allow_domains, vec!["api.anthropic.com".to_string()]);
+        assert_eq!(tight.allow_ports, vec![443]);
+        assert_eq!(tight.deny_domains, vec!["evil.anthropic.com".to_string()]);
+        assert_eq!(policy.tighter(&EgressPolicy::default()), policy);
+    }
+}
@@ -13,8 +13,12 @@
 //! the `oqto-egress-relay` binary: the PROXY v2 header encoder and the binary
 //! path resolver (mirroring [`crate::landlock_shim::resolve_shim_binary`]).
 //!
-//! The relay is pure mechanism: it carries no ACL or secrets. Policy
-//! (allow/deny, credential injection) lives entirely in eavs.
+//! The relay carries no secrets. When given an
+//! [`EgressPolicy`](crate::EgressPolicy) it refuses connections the policy
+//! blocks (see [`crate::egress_policy`]); credential injection lives in eavs.
+//!
+//! In a container's namespace (see [`crate::egress::attach_container`]) there
+//! is no eavs: the relay connects to the original destination itself.
 
 use std::env;
 use std::net::SocketAddr;
@@ -24,8 +28,15 @@ use std::path::PathBuf;
 pub const RELAY_BIN_OVERRIDE_ENV: &str = "OQTO_EGRESS_RELAY_BIN";
 
 /// Env var the relay reads for the eavs transparent endpoint (`ip:port`).
+/// Unset, the relay connects to the original destinations directly.
 pub const RELAY_EAVS_ENV: &str = "OQTO_EGRESS_RELAY_EAVS";
 
+/// Env var with a pid the relay exits after (the container it serves).
+pub const RELAY_WATCH_PID_ENV: &str = "OQTO_EGRESS_RELAY_WATCH_PID";
+
+/// `SO_MARK` of the relay's direct connections, exempted from the redirect.
+pub const RELAY_MARK: u32 = 0x6f71;
+
 /// Env var the relay reads for its in-namespace listen address (`ip:port`).
 pub const RELAY_LISTEN_ENV: &str = "OQTO_EGRESS_RELAY_LISTEN";
 
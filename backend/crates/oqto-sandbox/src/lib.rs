@@ -1,6 +1,7 @@
 pub mod cli;
 mod config;
 pub mod egress;
+pub mod egress_policy;
 pub mod egress_relay;
 pub mod landlock_shim;
 mod spawn;
@@ -8,8 +9,10 @@ mod workspace_config;
 
 pub use cli::run_cli;
 pub use config::{
-    GuardConfig, GuardPolicy, LandlockMode, NetworkConfig, NetworkMode, PromptConfig,
-    SandboxConfig, SandboxConfigFile, SandboxProfile, SeccompMode, SshProxyConfig,
+    FULL_SESSION_PROFILE, GuardConfig, GuardPolicy, LandlockMode, NetworkConfig, NetworkMode,
+    PromptConfig, ResourceConfig, SandboxConfig, SandboxConfigFile, SandboxProfile, SeccompMode,
+    SshProxyConfig,
 };
 pub use egress::{EgressGuard, EgressPlan, EgressProxy};
+pub use egress_policy::{BlockedConnection, EgressPolicy};
 pub use spawn::configure_bwrap_pre_exec;
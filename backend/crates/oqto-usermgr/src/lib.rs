@@ -1,8 +1,9 @@
 //! oqto-usermgr validation library.
 //!
-//! This is the public interface for the validation logic, used by:
+//! This is the public interface for the validation and quota logic, used by:
 //! - The oqto-usermgr daemon (main.rs)
 //! - Fuzz tests
 //! - Unit tests
 
+pub mod quota;
 pub mod validate;
@@ -18,6 +18,7 @@
 //! - Shell must be in allowlist
 //! - GECOS must start with "Oqto platform user "
 
+use oqto_usermgr::quota::{self, QuotaBackend};
 use oqto_usermgr::validate::*;
 use serde::{Deserialize, Serialize};
 use std::io::{BufRead, BufReader, Write};
@@ -224,8 +225,12 @@ fn dispatch(req: &Request) -> Response {
         "restart-service" => cmd_restart_service(&req.args),
         "run-as-user" => cmd_run_as_user(&req.args),
         "copy-dir" => cmd_copy_dir(&req.args),
+        "grant-path-access" => cmd_grant_path_access(&req.args),
+        "revoke-path-access" => cmd_revoke_path_access(&req.args),
         "fix-socket-dir" => cmd_fix_socket_dir(&req.args),
         "verify-socket-dirs" => cmd_verify_socket_dirs(&req.args),
+        "set-quota" => cmd_set_quota(&req.args),
+        "quota-report" => cmd_quota_report(&req.args),
         "ping" => Response::success(),
         other => Response::error(format!("unknown command: {other}")),
     }
@@ -314,6 +319,10 @@ fn get_u32(args: &serde_json::Value, key: &str) -> Result<u32, Response> {
         .ok_or_else(|| Response::error(format!("missing '{key}'")))
 }
 
+fn get_bool(args: &serde_json::Value, key: &str) -> bool {
+    args.get(key).and_then(|v| v.as_bool()).unwrap_or(false)
+}
+
 // --- Command handlers ---
 
 fn cmd_create_group(args: &serde_json::Value) -> Response {
@@ -1571,6 +1580,158 @@ fn cmd_copy_dir(args: &serde_json::Value) -> Response {
     Response::success()
 }
 
+/// Validate the owner/grantee/path triple shared by the path access commands.
+fn delegated_access_args(args: &serde_json::Value) -> Result<(&str, &str, &str), Response> {
+    let owner = get_str(args, "owner")?;
+    let grantee = get_str(args, "grantee")?;
+    let path = get_str(args, "path")?;
+
+    validate_username(grantee).map_err(|e| Response::error(format!("grantee: {e}")))?;
+    validate_delegated_path(owner, path).map_err(Response::error)?;
+    if owner == grantee {
+        return Err(Response::error("owner and grantee must differ"));
+    }
+    Ok((owner, grantee, path.trim_end_matches('/')))
+}
+
+/// Grant another platform user ACL access to a directory in `owner`'s
+/// workspace. Existing and newly created entries below `path` get the ACL;
+/// the ancestors only get traverse (`--x`), so nothing else in the owner's
+/// home becomes listable or readable.
+fn cmd_grant_path_access(args: &serde_json::Value) -> Response {
+    let (owner, grantee, path) = match delegated_access_args(args) {
+        Ok(v) => v,
+        Err(r) => return r,
+    };
+    let perms = match get_str(args, "mode")
+        .and_then(|m| validate_access_mode(m).map_err(Response::error))
+    {
+        Ok(p) => p,
+        Err(r) => return r,
+    };
+
+    // The validated path must be the real directory, not a symlink into
+    // somewhere else.
+    match std::fs::canonicalize(path) {
+        Ok(real) if real == std::path::Path::new(path) && real.is_dir() => {}
+        Ok(_) => return Response::error("path must be an existing directory without symlinks"),
+        Err(e) => return Response::error(format!("cannot resolve {path}: {e}")),
+    }
+
+    let traverse = format!("u:{grantee}:--x");
+    for dir in traverse_ancestors(owner, path) {
+        if let Err(e) = run_cmd("/usr/bin/setfacl", &["-m", &traverse, &dir]) {
+            return Response::error(format!("setfacl traverse on {dir}: {e}"));
+        }
+    }
+
+    let entry = format!("u:{grantee}:{perms}");
+    if let Err(e) = run_cmd("/usr/bin/setfacl", &["-R", "-P", "-m", &entry, path]) {
+        return Response::error(format!("setfacl failed: {e}"));
+    }
+    if let Err(e) = run_cmd("/usr/bin/setfacl", &["-R", "-P", "-d", "-m", &entry, path]) {
+        return Response::error(format!("setfacl default ACL failed: {e}"));
+    }
+    Response::success()
+}
+
+/// Remove a grant made by `grant-path-access`. Traverse entries on the
+/// ancestors are only removed when `remove_traverse` is set, because other
+/// grants from the same owner may still rely on them.
+fn cmd_revoke_path_access(args: &serde_json::Value) -> Response {
+    let (owner, grantee, path) = match delegated_access_args(args) {
+        Ok(v) => v,
+        Err(r) => return r,
+    };
+    let entry = format!("u:{grantee}");
+
+    if std::path::Path::new(path).is_dir() {
+        if let Err(e) = run_cmd("/usr/bin/setfacl", &["-R", "-P", "-x", &entry, path]) {
+            return Response::error(format!("setfacl failed: {e}"));
+        }
+        if let Err(e) = run_cmd("/usr/bin/setfacl", &["-R", "-P", "-d", "-x", &entry, path]) {
+            return Response::error(format!("setfacl default ACL failed: {e}"));
+        }
+    }
+
+    if get_bool(args, "remove_traverse") {
+        for dir in traverse_ancestors(owner, path) {
+            if std::path::Path::new(&dir).is_dir()
+                && let Err(e) = run_cmd("/usr/bin/setfacl", &["-x", &entry, &dir])
+            {
+                return Response::error(format!("setfacl traverse on {dir}: {e}"));
+            }
+        }
+    }
+    Response::success()
+}
+
+/// Mount point and quota backend of the filesystem holding `path`.
+fn quota_filesystem(path: &str) -> Result<(String, QuotaBackend), String> {
+    let output = run_cmd(
+        "/usr/bin/findmnt",
+        &["-n", "-o", "TARGET,FSTYPE", "--target", path],
+    )?;
+    let mut fields = output.split_whitespace();
+    match (fields.next(), fields.next()) {
+        (Some(target), Some(fs_type)) => {
+            Ok((target.to_string(), QuotaBackend::for_fs_type(fs_type)))
+        }
+        _ => Err(format!("cannot determine the filesystem of {path}")),
+    }
+}
+
+/// Limit a platform user's disk usage on the filesystem holding their home
+/// to `gb` GiB (0 removes the limit). The filesystem must have user quotas
+/// enabled (`uquota` on XFS, `usrquota` + `quotaon` elsewhere).
+fn cmd_set_quota(args: &serde_json::Value) -> Response {
+    let username = match get_str(args, "username") {
+        Ok(u) => u,
+        Err(r) => return r,
+    };
+    let gb = match args.get("gb").and_then(|v| v.as_u64()) {
+        Some(gb) => gb,
+        None => return Response::error("missing 'gb'"),
+    };
+    if let Err(e) = validate_username(username) {
+        return Response::error(e);
+    }
+    if let Err(e) = quota::validate_quota_gb(gb) {
+        return Response::error(e);
+    }
+    if get_user_uid(username).is_none() {
+        return Response::error(format!("user {username} does not exist"));
+    }
+
+    let (mount, backend) = match quota_filesystem(&format!("/home/{username}")) {
+        Ok(v) => v,
+        Err(e) => return Response::error(e),
+    };
+    let (cmd, cmd_args) = quota::set_quota_command(backend, username, gb, &mount);
+    let cmd_args: Vec<&str> = cmd_args.iter().map(String::as_str).collect();
+    match run_cmd(cmd, &cmd_args) {
+        Ok(_) => Response::success_with_data(serde_json::json!({ "mount": mount, "gb": gb })),
+        Err(e) => Response::error(format!("setting quota failed: {e}")),
+    }
+}
+
+/// Usage and limits of every platform user on the filesystem holding /home.
+fn cmd_quota_report(_args: &serde_json::Value) -> Response {
+    let (mount, backend) = match quota_filesystem("/home") {
+        Ok(v) => v,
+        Err(e) => return Response::error(e),
+    };
+    let (cmd, cmd_args) = quota::report_command(backend, &mount);
+    let cmd_args: Vec<&str> = cmd_args.iter().map(String::as_str).collect();
+    match run_cmd(cmd, &cmd_args) {
+        Ok(output) => {
+            let usage = quota::parse_report(backend, &output, USERNAME_PREFIX);
+            Response::success_with_data(serde_json::json!({ "mount": mount, "users": usage }))
+        }
+        Err(e) => Response::error(format!("quota report failed: {e}")),
+    }
+}
+
 fn cmd_run_as_user(args: &serde_json::Value) -> Response {
     let username = match get_str(args, "username") {
         Ok(s) => s,
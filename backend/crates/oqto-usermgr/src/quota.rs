@@ -0,0 +1,218 @@
+//! Disk quotas for platform users.
+//!
+//! Block limits are set with `xfs_quota` on XFS and with `setquota` on other
+//! filesystems (ext4 mounted with `usrquota`). Building the commands and
+//! parsing their reports is pure and lives here so it can be tested; the
+//! daemon only runs them.
+
+use serde::Serialize;
+
+/// Largest accepted quota in GiB.
+pub const QUOTA_GB_MAX: u64 = 100_000;
+
+/// Bytes per quota block (both tools report 1 KiB blocks).
+const BLOCK_BYTES: u64 = 1024;
+
+/// Quota tooling for a filesystem.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum QuotaBackend {
+    /// `xfs_quota`.
+    Xfs,
+    /// `setquota` / `repquota` (Linux VFS quotas).
+    Vfs,
+}
+
+impl QuotaBackend {
+    /// Backend for a filesystem type as printed by `findmnt -o FSTYPE`.
+    pub fn for_fs_type(fs_type: &str) -> Self {
+        if fs_type.trim() == "xfs" {
+            Self::Xfs
+        } else {
+            Self::Vfs
+        }
+    }
+}
+
+/// One user's usage and limits.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct QuotaUsage {
+    pub username: String,
+    pub used_bytes: u64,
+    /// 0 when no limit is set.
+    pub soft_limit_bytes: u64,
+    pub hard_limit_bytes: u64,
+}
+
+/// Validate a quota size. 0 removes the limit.
+pub fn validate_quota_gb(gb: u64) -> Result<(), String> {
+    if gb > QUOTA_GB_MAX {
+        return Err(format!(
+            "quota must be at most {QUOTA_GB_MAX} GiB, got {gb}"
+        ));
+    }
+    Ok(())
+}
+
+/// Command setting the soft and hard block limits of `username` on the
+/// filesystem mounted at `mount` to `gb` GiB.
+pub fn set_quota_command(
+    backend: QuotaBackend,
+    username: &str,
+    gb: u64,
+    mount: &str,
+) -> (&'static str, Vec<String>) {
+    match backend {
+        QuotaBackend::Xfs => (
+            "/usr/sbin/xfs_quota",
+            vec![
+                "-x".to_string(),
+                "-c".to_string(),
+                format!("limit -u bsoft={gb}g bhard={gb}g {username}"),
+                mount.to_string(),
+            ],
+        ),
+        QuotaBackend::Vfs => {
+            let blocks = (gb * 1024 * 1024 * 1024 / BLOCK_BYTES).to_string();
+            (
+                "/usr/sbin/setquota",
+                vec![
+                    "-u".to_string(),
+                    username.to_string(),
+                    blocks.clone(),
+                    blocks,
+                    "0".to_string(),
+                    "0".to_string(),
+                    mount.to_string(),
+                ],
+            )
+        }
+    }
+}
+
+/// Command printing the user quota report of the filesystem at `mount`.
+pub fn report_command(backend: QuotaBackend, mount: &str) -> (&'static str, Vec<String>) {
+    match backend {
+        QuotaBackend::Xfs => (
+            "/usr/sbin/xfs_quota",
+            vec![
+                "-x".to_string(),
+                "-c".to_string(),
+                "report -u -b -N".to_string(),
+                mount.to_string(),
+            ],
+        ),
+        QuotaBackend::Vfs => (
+            "/usr/sbin/repquota",
+            vec!["-u".to_string(), "-p".to_string(), mount.to_string()],
+        ),
+    }
+}
+
+/// Parse a quota report, keeping users whose name starts with `prefix`.
+///
+/// `xfs_quota report -b -N` lines are `user used soft hard warn/grace`;
+/// `repquota -p` lines are `user flags used soft hard grace ...`.
+pub fn parse_report(backend: QuotaBackend, output: &str, prefix: &str) -> Vec<QuotaUsage> {
+    let skip = match backend {
+        QuotaBackend::Xfs => 1,
+        QuotaBackend::Vfs => 2,
+    };
+    output
+        .lines()
+        .filter_map(|line| {
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            let username = *fields.first()?;
+            if !username.starts_with(prefix) {
+                return None;
+            }
+            let mut numbers = fields
+                .iter()
+                .skip(skip)
+                .map(|field| field.parse::<u64>().ok());
+            let used = numbers.next()??;
+            let soft = numbers.next()??;
+            let hard = numbers.next()??;
+            Some(QuotaUsage {
+                username: username.to_string(),
+                used_bytes: used * BLOCK_BYTES,
+                soft_limit_bytes: soft * BLOCK_BYTES,
+                hard_limit_bytes: hard * BLOCK_BYTES,
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn quota_gb_range() {
+        assert!(validate_quota_gb(0).is_ok());
+        assert!(validate_quota_gb(50).is_ok());
+        assert!(validate_quota_gb(QUOTA_GB_MAX + 1).is_err());
+    }
+
+    #[test]
+    fn set_quota_commands() {
+        let (cmd, args) = set_quota_command(QuotaBackend::Xfs, "oqto_alice", 10, "/home");
+        assert_eq!(cmd, "/usr/sbin/xfs_quota");
+        assert_eq!(args[2], "limit -u bsoft=10g bhard=10g oqto_alice");
+
+        let (cmd, args) = set_quota_command(QuotaBackend::Vfs, "oqto_alice", 10, "/home");
+        assert_eq!(cmd, "/usr/sbin/setquota");
+        assert_eq!(
+            args,
+            vec![
+                "-u",
+                "oqto_alice",
+                "10485760",
+                "10485760",
+                "0",
+                "0",
+                "/home"
+            ]
+        );
+    }
+
+    #[test]
+    fn parse_xfs_report() {
+        let output = "root            0          0          0     00 [--------]\n\
+                      oqto_alice   2048   10485760   10485760     00 [--------]\n\
+                      oqto_bob    12000          0          0     00 [--------]\n";
+        let usage = parse_report(QuotaBackend::Xfs, output, "oqto_");
+        assert_eq!(
+            usage,
+            vec![
+                QuotaUsage {
+                    username: "oqto_alice".to_string(),
+                    used_bytes: 2048 * 1024,
+                    soft_limit_bytes: 10 << 30,
+                    hard_limit_bytes: 10 << 30,
+                },
+                QuotaUsage {
+                    username: "oqto_bob".to_string(),
+                    used_bytes: 12000 * 1024,
+                    soft_limit_bytes: 0,
+                    hard_limit_bytes: 0,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn parse_repquota_report() {
+        let output = "*** Report for user quotas on device /dev/sda2\n\
+                      Block grace time: 7days; Inode grace time: 7days\n\
+                      \x20                       Block limits                File limits\n\
+                      User            used    soft    hard  grace    used  soft  hard  grace\n\
+                      ----------------------------------------------------------------------\n\
+                      root      --  120000       0       0      0    4000     0     0      0\n\
+                      oqto_alice +- 10485800 10485760 10485760 604800  120     0     0      0\n";
+        let usage = parse_report(QuotaBackend::Vfs, output, "oqto_");
+        assert_eq!(usage.len(), 1);
+        assert_eq!(usage[0].username, "oqto_alice");
+        assert_eq!(usage[0].used_bytes, 10485800 * 1024);
+        assert_eq!(usage[0].hard_limit_bytes, 10 << 30);
+    }
+}
@@ -39,6 +39,9 @@ pub const ALLOWED_SHELLS: &[&str] = &[
 /// Allowed chmod modes.
 pub const ALLOWED_MODES: &[&str] = &["700", "750", "755", "770", "2770"];
 
+/// Directory under a user's home that holds their workspaces.
+pub const WORKSPACE_DIR: &str = "oqto";
+
 /// Validate a username for use as a Linux user managed by oqto.
 ///
 /// Rules:
@@ -192,6 +195,62 @@ pub fn validate_chmod_mode(mode: &str) -> Result<(), String> {
     }
 }
 
+/// Map a delegated access mode to the ACL permissions it grants.
+///
+/// `X` only adds execute on directories (and files already executable by
+/// someone), so delegated access never makes plain files executable.
+pub fn validate_access_mode(mode: &str) -> Result<&'static str, String> {
+    match mode {
+        "ro" => Ok("rX"),
+        "rw" => Ok("rwX"),
+        other => Err(format!("access mode '{other}' must be 'ro' or 'rw'")),
+    }
+}
+
+/// Validate a directory `owner` delegates to another user.
+///
+/// Rules:
+/// - Must be strictly inside `/home/<owner>/oqto/` (never the home itself
+///   or the workspace root)
+/// - No hidden components (`.oqto`, `.ssh`, encrypted workspace stores, ...)
+/// - Plus all `validate_path` rules
+pub fn validate_delegated_path(owner: &str, path: &str) -> Result<(), String> {
+    validate_username(owner)?;
+    let root = format!("/home/{owner}/{WORKSPACE_DIR}/");
+    validate_path(path, &[root.as_str()])?;
+    let rest = path[root.len()..].trim_end_matches('/');
+    if rest.is_empty() {
+        return Err("path must be a directory inside the workspace root".into());
+    }
+    if rest.split('/').any(|c| c.starts_with('.')) {
+        return Err("path contains a hidden component".into());
+    }
+    Ok(())
+}
+
+/// Directories between the owner's home and `path` (exclusive) that need a
+/// traverse-only ACL entry so the grantee can reach `path`.
+///
+/// Expects `path` to have passed `validate_delegated_path`.
+pub fn traverse_ancestors(owner: &str, path: &str) -> Vec<String> {
+    let home = format!("/home/{owner}");
+    let mut dirs = Vec::new();
+    let mut current = std::path::Path::new(path.trim_end_matches('/'));
+    while let Some(parent) = current.parent() {
+        let parent_str = parent.to_string_lossy();
+        if !parent_str.starts_with(&home) {
+            break;
+        }
+        dirs.push(parent_str.into_owned());
+        if parent_str == home {
+            break;
+        }
+        current = parent;
+    }
+    dirs.reverse();
+    dirs
+}
+
 /// Validate all fields for a create-user request.
 /// Returns Ok(()) if all fields are valid, or the first error.
 pub fn validate_create_user(
@@ -571,6 +630,53 @@ mod tests {
         assert!(validate_chmod_mode("u+s").is_err());
     }
 
+    // ===== Delegated access validation =====
+
+    #[test]
+    fn access_mode_maps_to_acl_perms() {
+        assert_eq!(validate_access_mode("ro"), Ok("rX"));
+        assert_eq!(validate_access_mode("rw"), Ok("rwX"));
+        assert!(validate_access_mode("rwx").is_err());
+        assert!(validate_access_mode("").is_err());
+    }
+
+    #[test]
+    fn delegated_path_valid() {
+        assert!(validate_delegated_path("oqto_bob", "/home/oqto_bob/oqto/main").is_ok());
+        assert!(validate_delegated_path("oqto_bob", "/home/oqto_bob/oqto/app/src/").is_ok());
+    }
+
+    #[test]
+    fn delegated_path_rejects_outside_workspace() {
+        assert!(validate_delegated_path("oqto_bob", "/home/oqto_bob").is_err());
+        assert!(validate_delegated_path("oqto_bob", "/home/oqto_bob/oqto").is_err());
+        assert!(validate_delegated_path("oqto_bob", "/home/oqto_bob/oqto/").is_err());
+        assert!(validate_delegated_path("oqto_bob", "/home/oqto_bob/.ssh").is_err());
+        assert!(validate_delegated_path("oqto_bob", "/home/oqto_alice/oqto/main").is_err());
+        assert!(validate_delegated_path("oqto_bob", "/home/oqto_bob/oqto/../.ssh").is_err());
+        assert!(validate_delegated_path("root", "/home/root/oqto/main").is_err());
+    }
+
+    #[test]
+    fn delegated_path_rejects_hidden_components() {
+        assert!(validate_delegated_path("oqto_bob", "/home/oqto_bob/oqto/main/.oqto").is_err());
+        assert!(
+            validate_delegated_path("oqto_bob", "/home/oqto_bob/oqto/.main.oqto-crypt").is_err()
+        );
+    }
+
+    #[test]
+    fn traverse_ancestors_stop_at_home() {
+        assert_eq!(
+            traverse_ancestors("oqto_bob", "/home/oqto_bob/oqto/app/src"),
+            vec![
+                "/home/oqto_bob".to_string(),
+                "/home/oqto_bob/oqto".to_string(),
+                "/home/oqto_bob/oqto/app".to_string(),
+            ]
+        );
+    }
+
     // ===== Composite create-user validation =====
 
     #[test]
@@ -0,0 +1,205 @@
+-- Tables shared by all backend replicas when [db] url points at Postgres.
+--
+-- Mirrors the final shape of the SQLite migrations for these tables.
+-- Timestamps stay TEXT in the format of SQLite's datetime('now') so both
+-- backends store and compare them the same way; integer columns read as
+-- i64 are BIGINT.
+
+CREATE TABLE IF NOT EXISTS users (
+    id TEXT PRIMARY KEY NOT NULL,
+    external_id TEXT UNIQUE,
+    username TEXT UNIQUE NOT NULL,
+    email TEXT UNIQUE NOT NULL,
+    password_hash TEXT,
+    display_name TEXT NOT NULL,
+    avatar_url TEXT,
+    role TEXT NOT NULL DEFAULT 'user' CHECK (role IN ('user', 'admin', 'service')),
+    is_active BOOLEAN NOT NULL DEFAULT TRUE,
+    created_at TEXT NOT NULL DEFAULT to_char(now() AT TIME ZONE 'UTC', 'YYYY-MM-DD HH24:MI:SS'),
+    updated_at TEXT NOT NULL DEFAULT to_char(now() AT TIME ZONE 'UTC', 'YYYY-MM-DD HH24:MI:SS'),
+    last_login_at TEXT,
+    settings TEXT DEFAULT '{}',
+    mmry_port BIGINT,
+    sldr_port BIGINT,
+    linux_username TEXT,
+    linux_uid BIGINT
+);
+
+CREATE INDEX IF NOT EXISTS idx_users_role ON users(role);
+CREATE INDEX IF NOT EXISTS idx_users_is_active ON users(is_active);
+CREATE UNIQUE INDEX IF NOT EXISTS idx_users_mmry_port ON users(mmry_port) WHERE mmry_port IS NOT NULL;
+CREATE UNIQUE INDEX IF NOT EXISTS idx_users_sldr_port ON users(sldr_port) WHERE sldr_port IS NOT NULL;
+CREATE UNIQUE INDEX IF NOT EXISTS idx_users_linux_username ON users(linux_username) WHERE linux_username IS NOT NULL;
+CREATE INDEX IF NOT EXISTS idx_users_linux_uid ON users(linux_uid) WHERE linux_uid IS NOT NULL;
+
+CREATE TABLE IF NOT EXISTS sessions (
+    id TEXT PRIMARY KEY NOT NULL,
+    readable_id TEXT,
+    container_id TEXT,
+    container_name TEXT NOT NULL,
+    user_id TEXT NOT NULL DEFAULT 'default',
+    workspace_path TEXT NOT NULL,
+    agent TEXT,
+    image TEXT NOT NULL,
+    image_digest TEXT,
+    agent_port BIGINT NOT NULL,
+    fileserver_port BIGINT NOT NULL,
+    ttyd_port BIGINT NOT NULL,
+    eavs_port BIGINT,
+    agent_base_port BIGINT,
+    max_agents BIGINT DEFAULT 10,
+    eavs_key_id TEXT,
+    eavs_key_hash TEXT,
+    eavs_virtual_key TEXT,
+    mmry_port BIGINT,
+    fileserver_token_secret TEXT,
+    status TEXT NOT NULL DEFAULT 'pending' CHECK (status IN ('pending', 'starting', 'running', 'stopping', 'stopped', 'failed')),
+    runtime_mode TEXT NOT NULL DEFAULT 'container' CHECK (runtime_mode IN ('container', 'local')),
+    created_at TEXT NOT NULL DEFAULT to_char(now() AT TIME ZONE 'UTC', 'YYYY-MM-DD HH24:MI:SS'),
+    started_at TEXT,
+    stopped_at TEXT,
+    last_activity_at TEXT,
+    error_message TEXT
+);
+
+CREATE INDEX IF NOT EXISTS idx_sessions_status ON sessions(status);
+CREATE INDEX IF NOT EXISTS idx_sessions_user_id ON sessions(user_id);
+CREATE INDEX IF NOT EXISTS idx_sessions_container_id ON sessions(container_id);
+CREATE INDEX IF NOT EXISTS idx_sessions_eavs_key_id ON sessions(eavs_key_id);
+CREATE UNIQUE INDEX IF NOT EXISTS idx_sessions_readable_id ON sessions(readable_id);
+CREATE INDEX IF NOT EXISTS idx_sessions_image_digest ON sessions(image_digest);
+CREATE INDEX IF NOT EXISTS idx_sessions_runtime_mode ON sessions(runtime_mode);
+CREATE INDEX IF NOT EXISTS idx_sessions_last_activity ON sessions(last_activity_at);
+CREATE UNIQUE INDEX IF NOT EXISTS idx_sessions_active_agent_port
+ON sessions(agent_port)
+WHERE status IN ('pending', 'starting', 'running');
+CREATE UNIQUE INDEX IF NOT EXISTS idx_sessions_active_fileserver_port
+ON sessions(fileserver_port)
+WHERE status IN ('pending', 'starting', 'running');
+CREATE UNIQUE INDEX IF NOT EXISTS idx_sessions_active_ttyd_port
+ON sessions(ttyd_port)
+WHERE status IN ('pending', 'starting', 'running');
+CREATE UNIQUE INDEX IF NOT EXISTS idx_sessions_active_eavs_port
+ON sessions(eavs_port)
+WHERE status IN ('pending', 'starting', 'running') AND eavs_port IS NOT NULL;
+CREATE UNIQUE INDEX IF NOT EXISTS idx_sessions_active_mmry_port
+ON sessions(mmry_port)
+WHERE runtime_mode = 'container'
+  AND status IN ('pending', 'starting', 'running')
+  AND mmry_port IS NOT NULL;
+
+CREATE TABLE IF NOT EXISTS agents (
+    id TEXT PRIMARY KEY NOT NULL,
+    session_id TEXT NOT NULL REFERENCES sessions(id) ON DELETE CASCADE,
+    agent_id TEXT NOT NULL,
+    name TEXT NOT NULL,
+    directory TEXT NOT NULL,
+    internal_port BIGINT NOT NULL,
+    external_port BIGINT NOT NULL,
+    status TEXT NOT NULL DEFAULT 'stopped' CHECK (status IN ('running', 'starting', 'stopped', 'failed')),
+    has_agents_md BOOLEAN NOT NULL DEFAULT FALSE,
+    has_git BOOLEAN NOT NULL DEFAULT FALSE,
+    created_at TEXT NOT NULL DEFAULT to_char(now() AT TIME ZONE 'UTC', 'YYYY-MM-DD HH24:MI:SS'),
+    started_at TEXT,
+    stopped_at TEXT,
+    UNIQUE(session_id, agent_id)
+);
+
+CREATE INDEX IF NOT EXISTS idx_agents_session_id ON agents(session_id);
+CREATE INDEX IF NOT EXISTS idx_agents_status ON agents(status);
+CREATE UNIQUE INDEX IF NOT EXISTS idx_agents_external_port_active
+ON agents(external_port)
+WHERE status IN ('running', 'starting');
+
+CREATE TABLE IF NOT EXISTS invite_codes (
+    id TEXT PRIMARY KEY NOT NULL,
+    code TEXT UNIQUE NOT NULL,
+    created_by TEXT NOT NULL REFERENCES users(id),
+    used_by TEXT REFERENCES users(id),
+    uses_remaining INTEGER NOT NULL DEFAULT 1,
+    max_uses INTEGER NOT NULL DEFAULT 1,
+    expires_at TEXT,
+    created_at TEXT NOT NULL DEFAULT to_char(now() AT TIME ZONE 'UTC', 'YYYY-MM-DD HH24:MI:SS'),
+    last_used_at TEXT,
+    note TEXT
+);
+
+CREATE INDEX IF NOT EXISTS idx_invite_codes_created_by ON invite_codes(created_by);
+CREATE INDEX IF NOT EXISTS idx_invite_codes_expires_at ON invite_codes(expires_at);
+CREATE INDEX IF NOT EXISTS idx_invite_codes_uses_remaining ON invite_codes(uses_remaining);
+
+CREATE TABLE IF NOT EXISTS invite_code_redemptions (
+    id TEXT PRIMARY KEY,
+    invite_code_id TEXT NOT NULL REFERENCES invite_codes(id) ON DELETE CASCADE,
+    user_id TEXT REFERENCES users(id) ON DELETE SET NULL,
+    username TEXT NOT NULL,
+    redeemed_at TEXT NOT NULL DEFAULT to_char(now() AT TIME ZONE 'UTC', 'YYYY-MM-DD HH24:MI:SS'),
+    -- Insertion order, for redemptions within the same second.
+    seq BIGSERIAL
+);
+
+CREATE INDEX IF NOT EXISTS idx_invite_code_redemptions_code ON invite_code_redemptions(invite_code_id, redeemed_at);
+
+CREATE TABLE IF NOT EXISTS workspace_locations (
+    id TEXT PRIMARY KEY NOT NULL,
+    user_id TEXT NOT NULL,
+    workspace_id TEXT NOT NULL,
+    location_id TEXT NOT NULL,
+    kind TEXT NOT NULL CHECK (kind IN ('local', 'remote')),
+    path TEXT NOT NULL,
+    runner_id TEXT,
+    repo_fingerprint TEXT,
+    is_active BIGINT NOT NULL DEFAULT 0,
+    created_at TEXT NOT NULL DEFAULT to_char(now() AT TIME ZONE 'UTC', 'YYYY-MM-DD HH24:MI:SS'),
+    updated_at TEXT NOT NULL DEFAULT to_char(now() AT TIME ZONE 'UTC', 'YYYY-MM-DD HH24:MI:SS'),
+    UNIQUE(user_id, workspace_id, location_id)
+);
+
+CREATE INDEX IF NOT EXISTS idx_workspace_locations_active
+    ON workspace_locations(user_id, workspace_id, is_active);
+
+CREATE TABLE IF NOT EXISTS api_keys (
+    id TEXT PRIMARY KEY NOT NULL,
+    user_id TEXT NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    name TEXT NOT NULL,
+    key_prefix TEXT NOT NULL,
+    key_hash TEXT NOT NULL,
+    scopes TEXT NOT NULL DEFAULT '[]',
+    last_used_at TEXT,
+    expires_at TEXT,
+    created_at TEXT NOT NULL DEFAULT to_char(now() AT TIME ZONE 'UTC', 'YYYY-MM-DD HH24:MI:SS'),
+    revoked_at TEXT
+);
+
+CREATE UNIQUE INDEX IF NOT EXISTS idx_api_keys_hash ON api_keys(key_hash);
+CREATE INDEX IF NOT EXISTS idx_api_keys_user_id ON api_keys(user_id);
+CREATE INDEX IF NOT EXISTS idx_api_keys_prefix ON api_keys(key_prefix);
+CREATE INDEX IF NOT EXISTS idx_api_keys_revoked_at ON api_keys(revoked_at);
+
+CREATE TABLE IF NOT EXISTS shared_workspaces (
+    id TEXT PRIMARY KEY NOT NULL,
+    name TEXT NOT NULL,
+    slug TEXT UNIQUE NOT NULL,
+    linux_user TEXT NOT NULL,
+    path TEXT NOT NULL,
+    owner_id TEXT NOT NULL REFERENCES users(id),
+    description TEXT,
+    icon TEXT NOT NULL DEFAULT 'users',
+    color TEXT NOT NULL DEFAULT '#6366f1',
+    created_at TEXT NOT NULL DEFAULT to_char(now() AT TIME ZONE 'UTC', 'YYYY-MM-DD HH24:MI:SS'),
+    updated_at TEXT NOT NULL DEFAULT to_char(now() AT TIME ZONE 'UTC', 'YYYY-MM-DD HH24:MI:SS')
+);
+
+CREATE INDEX IF NOT EXISTS idx_shared_workspaces_owner ON shared_workspaces(owner_id);
+
+CREATE TABLE IF NOT EXISTS shared_workspace_members (
+    shared_workspace_id TEXT NOT NULL REFERENCES shared_workspaces(id) ON DELETE CASCADE,
+    user_id TEXT NOT NULL REFERENCES users(id),
+    role TEXT NOT NULL DEFAULT 'member' CHECK (role IN ('owner', 'admin', 'member', 'viewer')),
+    added_at TEXT NOT NULL DEFAULT to_char(now() AT TIME ZONE 'UTC', 'YYYY-MM-DD HH24:MI:SS'),
+    added_by TEXT REFERENCES users(id),
+    permissions TEXT,
+    PRIMARY KEY (shared_workspace_id, user_id)
+);
+
+CREATE INDEX IF NOT EXISTS idx_shared_workspace_members_user ON shared_workspace_members(user_id);
@@ -0,0 +1,13 @@
+-- Per-session setup beyond the container (see the SQLite migration).
+
+CREATE TABLE IF NOT EXISTS session_setups (
+    session_id TEXT PRIMARY KEY NOT NULL REFERENCES sessions(id) ON DELETE CASCADE,
+    cloned_from TEXT,
+    workspace TEXT,
+    provider TEXT,
+    model TEXT,
+    env TEXT NOT NULL DEFAULT '{}',
+    created_at TEXT NOT NULL DEFAULT to_char(now() AT TIME ZONE 'UTC', 'YYYY-MM-DD HH24:MI:SS')
+);
+
+CREATE INDEX IF NOT EXISTS idx_session_setups_cloned_from ON session_setups(cloned_from);
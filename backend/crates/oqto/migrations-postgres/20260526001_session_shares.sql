@@ -0,0 +1,14 @@
+-- Sessions shared with other users (see the SQLite migration).
+
+CREATE TABLE IF NOT EXISTS session_shares (
+    session_id TEXT NOT NULL,
+    owner_user_id TEXT NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    user_id TEXT NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    access TEXT NOT NULL CHECK (access IN ('read', 'write')),
+    created_at TEXT NOT NULL DEFAULT to_char(now() AT TIME ZONE 'UTC', 'YYYY-MM-DD HH24:MI:SS'),
+    updated_at TEXT NOT NULL DEFAULT to_char(now() AT TIME ZONE 'UTC', 'YYYY-MM-DD HH24:MI:SS'),
+    PRIMARY KEY (session_id, user_id)
+);
+
+CREATE INDEX IF NOT EXISTS idx_session_shares_user ON session_shares(user_id);
+CREATE INDEX IF NOT EXISTS idx_session_shares_owner ON session_shares(owner_user_id);
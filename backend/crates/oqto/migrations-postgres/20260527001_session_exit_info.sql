@@ -0,0 +1,4 @@
+-- Structured diagnostics of why a session last stopped: exit code, signal,
+-- failure category and a hint for users.
+
+ALTER TABLE sessions ADD COLUMN IF NOT EXISTS exit_info JSONB;
@@ -0,0 +1,10 @@
+-- In-progress prompts shared between a user's devices (see the SQLite
+-- migration).
+
+CREATE TABLE IF NOT EXISTS prompt_drafts (
+    user_id TEXT NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    session_id TEXT NOT NULL,
+    state TEXT NOT NULL,
+    updated_at TEXT NOT NULL DEFAULT to_char(now() AT TIME ZONE 'UTC', 'YYYY-MM-DD HH24:MI:SS'),
+    PRIMARY KEY (user_id, session_id)
+);
@@ -0,0 +1,12 @@
+-- HTTPS credentials used when pushing workspace repositories (see the
+-- SQLite migration).
+
+CREATE TABLE IF NOT EXISTS git_credentials (
+    user_id TEXT NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    host TEXT NOT NULL,
+    username TEXT NOT NULL,
+    token TEXT NOT NULL,
+    created_at TEXT NOT NULL DEFAULT to_char(now() AT TIME ZONE 'UTC', 'YYYY-MM-DD HH24:MI:SS'),
+    updated_at TEXT NOT NULL DEFAULT to_char(now() AT TIME ZONE 'UTC', 'YYYY-MM-DD HH24:MI:SS'),
+    PRIMARY KEY (user_id, host)
+);
@@ -0,0 +1,10 @@
+-- Container resource limits of sessions and per-user defaults (see the
+-- SQLite migration).
+
+ALTER TABLE sessions ADD COLUMN IF NOT EXISTS resource_limits JSONB;
+
+CREATE TABLE IF NOT EXISTS user_resource_limits (
+    user_id TEXT PRIMARY KEY REFERENCES users(id) ON DELETE CASCADE,
+    limits JSONB NOT NULL,
+    updated_at TEXT NOT NULL DEFAULT to_char(now() AT TIME ZONE 'UTC', 'YYYY-MM-DD HH24:MI:SS')
+);
@@ -0,0 +1,21 @@
+-- Open self-service registrations (see the SQLite migration).
+
+CREATE TABLE IF NOT EXISTS registrations (
+    user_id TEXT PRIMARY KEY REFERENCES users(id) ON DELETE CASCADE,
+    email TEXT NOT NULL,
+    client_ip TEXT,
+    status TEXT NOT NULL CHECK (status IN ('pending_approval', 'active', 'rejected')),
+    quarantined BOOLEAN NOT NULL DEFAULT FALSE,
+    verification_token_hash TEXT,
+    verification_expires_at TEXT,
+    verified_at TEXT,
+    reviewed_by TEXT,
+    reviewed_at TEXT,
+    created_at TEXT NOT NULL DEFAULT to_char(now() AT TIME ZONE 'UTC', 'YYYY-MM-DD HH24:MI:SS')
+);
+
+CREATE INDEX IF NOT EXISTS idx_registrations_created ON registrations(created_at);
+CREATE INDEX IF NOT EXISTS idx_registrations_ip ON registrations(client_ip, created_at);
+CREATE INDEX IF NOT EXISTS idx_registrations_status ON registrations(status);
+CREATE UNIQUE INDEX IF NOT EXISTS idx_registrations_token
+    ON registrations(verification_token_hash);
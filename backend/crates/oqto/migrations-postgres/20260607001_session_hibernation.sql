@@ -0,0 +1,3 @@
+-- Hibernation snapshot of idle sessions (see the SQLite migration).
+
+ALTER TABLE sessions ADD COLUMN IF NOT EXISTS hibernation JSONB;
@@ -0,0 +1,37 @@
+-- Session drafts and their reviewers (see the SQLite migration).
+
+CREATE TABLE IF NOT EXISTS session_drafts (
+    id TEXT PRIMARY KEY,
+    user_id TEXT NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    name TEXT,
+    workspace_path TEXT,
+    image TEXT,
+    agent TEXT,
+    provider TEXT,
+    model TEXT,
+    env TEXT NOT NULL DEFAULT '{}',
+    mounts TEXT NOT NULL DEFAULT '[]',
+    prompt TEXT,
+    start_at TEXT,
+    status TEXT NOT NULL DEFAULT 'draft'
+        CHECK (status IN ('draft', 'starting', 'started', 'failed')),
+    session_id TEXT,
+    chat_session_id TEXT,
+    error TEXT,
+    created_at TEXT NOT NULL DEFAULT to_char(now() AT TIME ZONE 'UTC', 'YYYY-MM-DD HH24:MI:SS'),
+    updated_at TEXT NOT NULL DEFAULT to_char(now() AT TIME ZONE 'UTC', 'YYYY-MM-DD HH24:MI:SS')
+);
+
+CREATE INDEX IF NOT EXISTS idx_session_drafts_user ON session_drafts(user_id);
+CREATE INDEX IF NOT EXISTS idx_session_drafts_due ON session_drafts(status, start_at);
+
+CREATE TABLE IF NOT EXISTS session_draft_shares (
+    draft_id TEXT NOT NULL REFERENCES session_drafts(id) ON DELETE CASCADE,
+    user_id TEXT NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    created_at TEXT NOT NULL DEFAULT to_char(now() AT TIME ZONE 'UTC', 'YYYY-MM-DD HH24:MI:SS'),
+    PRIMARY KEY (draft_id, user_id)
+);
+
+CREATE INDEX IF NOT EXISTS idx_session_draft_shares_user ON session_draft_shares(user_id);
+
+ALTER TABLE session_setups ADD COLUMN IF NOT EXISTS mounts TEXT;
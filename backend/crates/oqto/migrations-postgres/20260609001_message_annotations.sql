@@ -0,0 +1,23 @@
+-- Reactions, flags and notes on agent responses (see the SQLite migration).
+
+CREATE TABLE IF NOT EXISTS message_annotations (
+    id TEXT PRIMARY KEY,
+    session_id TEXT NOT NULL,
+    message_id TEXT NOT NULL,
+    user_id TEXT NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    workspace_id TEXT,
+    kind TEXT NOT NULL CHECK (kind IN ('reaction', 'flag', 'note')),
+    value TEXT NOT NULL,
+    provider TEXT,
+    model TEXT,
+    created_at TEXT NOT NULL DEFAULT to_char(now() AT TIME ZONE 'UTC', 'YYYY-MM-DD HH24:MI:SS'),
+    updated_at TEXT NOT NULL DEFAULT to_char(now() AT TIME ZONE 'UTC', 'YYYY-MM-DD HH24:MI:SS')
+);
+
+CREATE INDEX IF NOT EXISTS idx_message_annotations_session ON message_annotations(session_id, message_id);
+CREATE INDEX IF NOT EXISTS idx_message_annotations_model ON message_annotations(provider, model);
+
+CREATE UNIQUE INDEX IF NOT EXISTS idx_message_annotations_reaction
+    ON message_annotations(session_id, message_id, user_id) WHERE kind = 'reaction';
+CREATE UNIQUE INDEX IF NOT EXISTS idx_message_annotations_flag
+    ON message_annotations(session_id, message_id, user_id, value) WHERE kind = 'flag';
@@ -0,0 +1,19 @@
+-- Admin impersonation of users (see the SQLite migration).
+
+CREATE TABLE IF NOT EXISTS impersonations (
+    id TEXT PRIMARY KEY,
+    admin_id TEXT NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    user_id TEXT NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    reason TEXT NOT NULL,
+    break_glass BOOLEAN NOT NULL DEFAULT FALSE,
+    status TEXT NOT NULL CHECK (status IN ('pending', 'active', 'denied', 'ended')),
+    duration_minutes BIGINT NOT NULL,
+    requested_at TEXT NOT NULL DEFAULT to_char(now() AT TIME ZONE 'UTC', 'YYYY-MM-DD HH24:MI:SS'),
+    started_at TEXT,
+    expires_at TEXT NOT NULL,
+    ended_at TEXT,
+    ended_by TEXT
+);
+
+CREATE INDEX IF NOT EXISTS idx_impersonations_user ON impersonations(user_id, requested_at);
+CREATE INDEX IF NOT EXISTS idx_impersonations_admin ON impersonations(admin_id, requested_at);
@@ -0,0 +1,19 @@
+-- Smoke-check runs of workspace templates (see the SQLite migration).
+
+CREATE TABLE IF NOT EXISTS template_test_runs (
+    id TEXT PRIMARY KEY,
+    template TEXT NOT NULL,
+    version TEXT NOT NULL,
+    git_commit TEXT,
+    command TEXT NOT NULL,
+    status TEXT NOT NULL CHECK (status IN ('passed', 'failed', 'timed_out', 'error')),
+    exit_code BIGINT,
+    output TEXT NOT NULL DEFAULT '',
+    output_truncated BOOLEAN NOT NULL DEFAULT FALSE,
+    duration_ms BIGINT NOT NULL DEFAULT 0,
+    started_by TEXT REFERENCES users(id) ON DELETE SET NULL,
+    created_at TEXT NOT NULL DEFAULT to_char(now() AT TIME ZONE 'UTC', 'YYYY-MM-DD HH24:MI:SS')
+);
+
+CREATE INDEX IF NOT EXISTS idx_template_test_runs_template
+    ON template_test_runs(template, created_at);
@@ -0,0 +1,15 @@
+-- Messages and message parts private to the session owner (see the SQLite
+-- migration).
+
+CREATE TABLE IF NOT EXISTS private_messages (
+    id TEXT PRIMARY KEY,
+    session_id TEXT NOT NULL,
+    message_id TEXT NOT NULL,
+    part_id TEXT NOT NULL DEFAULT '',
+    owner_user_id TEXT NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    workspace_id TEXT,
+    created_at TEXT NOT NULL DEFAULT to_char(now() AT TIME ZONE 'UTC', 'YYYY-MM-DD HH24:MI:SS'),
+    UNIQUE (session_id, message_id, part_id)
+);
+
+CREATE INDEX IF NOT EXISTS idx_private_messages_session ON private_messages(session_id);
@@ -0,0 +1,15 @@
+-- Sealed agent secrets injected into sessions (see the SQLite migration).
+
+CREATE TABLE IF NOT EXISTS secrets (
+    id TEXT PRIMARY KEY,
+    user_id TEXT NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    workspace_path TEXT NOT NULL DEFAULT '',
+    kind TEXT NOT NULL CHECK (kind IN ('env', 'file')),
+    name TEXT NOT NULL,
+    sealed_value TEXT NOT NULL,
+    created_at TEXT NOT NULL DEFAULT to_char(now() AT TIME ZONE 'UTC', 'YYYY-MM-DD HH24:MI:SS'),
+    updated_at TEXT NOT NULL DEFAULT to_char(now() AT TIME ZONE 'UTC', 'YYYY-MM-DD HH24:MI:SS'),
+    UNIQUE (user_id, workspace_path, kind, name)
+);
+
+CREATE INDEX IF NOT EXISTS idx_secrets_user ON secrets(user_id);
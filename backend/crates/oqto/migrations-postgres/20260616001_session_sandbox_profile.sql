@@ -0,0 +1,3 @@
+-- Sandbox profile of container sessions (see the SQLite migration).
+
+ALTER TABLE session_setups ADD COLUMN IF NOT EXISTS sandbox_profile TEXT;
@@ -0,0 +1,42 @@
+-- Inbound triggers and their runs (see the SQLite migration), shared so a
+-- trigger URL works on every replica.
+
+CREATE TABLE IF NOT EXISTS agent_triggers (
+    id TEXT PRIMARY KEY,
+    user_id TEXT NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    name TEXT NOT NULL,
+    token_hash TEXT NOT NULL UNIQUE,
+    token_prefix TEXT NOT NULL,
+    workspace_path TEXT NOT NULL,
+    prompt TEXT NOT NULL,
+    template TEXT,
+    harness TEXT,
+    provider TEXT,
+    model TEXT,
+    timeout_secs BIGINT,
+    allowed_senders TEXT NOT NULL DEFAULT '[]',
+    reply_webhook_url TEXT,
+    reply_email BOOLEAN NOT NULL DEFAULT FALSE,
+    enabled BOOLEAN NOT NULL DEFAULT TRUE,
+    last_fired_at TEXT,
+    created_at TEXT NOT NULL DEFAULT to_char(now() AT TIME ZONE 'UTC', 'YYYY-MM-DD HH24:MI:SS'),
+    updated_at TEXT NOT NULL DEFAULT to_char(now() AT TIME ZONE 'UTC', 'YYYY-MM-DD HH24:MI:SS'),
+    UNIQUE (user_id, name)
+);
+
+CREATE TABLE IF NOT EXISTS trigger_runs (
+    id TEXT PRIMARY KEY,
+    trigger_id TEXT NOT NULL REFERENCES agent_triggers(id) ON DELETE CASCADE,
+    source TEXT NOT NULL,
+    sender TEXT,
+    subject TEXT,
+    status TEXT NOT NULL,
+    session_id TEXT NOT NULL,
+    error TEXT,
+    delivery TEXT,
+    created_at TEXT NOT NULL,
+    started_at TEXT,
+    finished_at TEXT
+);
+
+CREATE INDEX IF NOT EXISTS idx_trigger_runs_trigger ON trigger_runs(trigger_id, created_at);
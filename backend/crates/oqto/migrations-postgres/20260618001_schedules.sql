@@ -0,0 +1,58 @@
+-- Schedule run history and agent schedules (see the SQLite migrations),
+-- shared so replicas agree on which runs are due and who ran them.
+
+CREATE TABLE IF NOT EXISTS schedule_runs (
+    id TEXT NOT NULL,
+    user_id TEXT NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    schedule_name TEXT NOT NULL,
+    status TEXT NOT NULL,
+    scheduled_for TEXT,
+    started_at TEXT,
+    ended_at TEXT,
+    exit_code INTEGER,
+    log_path TEXT,
+    cost_usd DOUBLE PRECISION,
+    error TEXT,
+    session_id TEXT,
+    run_at TEXT NOT NULL,
+    PRIMARY KEY (user_id, id)
+);
+
+CREATE INDEX IF NOT EXISTS idx_schedule_runs_schedule ON schedule_runs(user_id, schedule_name, run_at);
+CREATE INDEX IF NOT EXISTS idx_schedule_runs_run_at ON schedule_runs(run_at);
+
+CREATE TABLE IF NOT EXISTS schedule_definitions (
+    user_id TEXT NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    name TEXT NOT NULL,
+    schedule TEXT NOT NULL,
+    updated_at TEXT NOT NULL,
+    PRIMARY KEY (user_id, name)
+);
+
+-- Last time any replica was known to be up.
+CREATE TABLE IF NOT EXISTS scheduler_heartbeat (
+    id INTEGER PRIMARY KEY CHECK (id = 1),
+    beat_at TEXT NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS agent_schedules (
+    id TEXT PRIMARY KEY,
+    user_id TEXT NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    name TEXT NOT NULL,
+    cron TEXT NOT NULL,
+    workspace_path TEXT NOT NULL,
+    prompt TEXT NOT NULL,
+    template TEXT,
+    harness TEXT,
+    provider TEXT,
+    model TEXT,
+    timeout_secs BIGINT,
+    enabled BOOLEAN NOT NULL DEFAULT TRUE,
+    next_run_at TEXT,
+    last_run_at TEXT,
+    created_at TEXT NOT NULL DEFAULT to_char(now() AT TIME ZONE 'UTC', 'YYYY-MM-DD HH24:MI:SS'),
+    updated_at TEXT NOT NULL DEFAULT to_char(now() AT TIME ZONE 'UTC', 'YYYY-MM-DD HH24:MI:SS'),
+    UNIQUE (user_id, name)
+);
+
+CREATE INDEX IF NOT EXISTS idx_agent_schedules_due ON agent_schedules(enabled, next_run_at);
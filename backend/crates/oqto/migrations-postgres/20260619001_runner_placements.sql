@@ -0,0 +1,12 @@
+-- Runner host of each federated session (see the SQLite migration), shared
+-- so every replica routes a session to the same host.
+
+CREATE TABLE IF NOT EXISTS runner_placements (
+    session_id TEXT PRIMARY KEY,
+    user_id TEXT NOT NULL,
+    runner_id TEXT NOT NULL,
+    created_at TEXT NOT NULL DEFAULT to_char(now() AT TIME ZONE 'UTC', 'YYYY-MM-DD HH24:MI:SS'),
+    updated_at TEXT NOT NULL DEFAULT to_char(now() AT TIME ZONE 'UTC', 'YYYY-MM-DD HH24:MI:SS')
+);
+
+CREATE INDEX IF NOT EXISTS idx_runner_placements_runner ON runner_placements(runner_id);
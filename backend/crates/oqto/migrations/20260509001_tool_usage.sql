@@ -0,0 +1,30 @@
+-- Tool-call usage statistics aggregated from canonical agent events
+
+-- Dedup log: one row per observed tool call. The same call can be seen by
+-- several WebSocket connections subscribed to one session.
+CREATE TABLE IF NOT EXISTS tool_call_log (
+    session_id TEXT NOT NULL,
+    tool_call_id TEXT NOT NULL,
+    recorded_at TEXT NOT NULL DEFAULT (datetime('now')),
+    PRIMARY KEY (session_id, tool_call_id)
+);
+
+CREATE INDEX IF NOT EXISTS idx_tool_call_log_recorded_at ON tool_call_log(recorded_at);
+
+-- Daily rollups per user / agent harness / project (workspace path) / tool.
+CREATE TABLE IF NOT EXISTS tool_usage_daily (
+    day TEXT NOT NULL,
+    user_id TEXT NOT NULL,
+    agent TEXT NOT NULL,
+    project TEXT NOT NULL,
+    tool_name TEXT NOT NULL,
+    calls INTEGER NOT NULL DEFAULT 0,
+    failures INTEGER NOT NULL DEFAULT 0,
+    timed_calls INTEGER NOT NULL DEFAULT 0,
+    total_duration_ms INTEGER NOT NULL DEFAULT 0,
+    updated_at TEXT NOT NULL DEFAULT (datetime('now')),
+    PRIMARY KEY (day, user_id, agent, project, tool_name)
+);
+
+CREATE INDEX IF NOT EXISTS idx_tool_usage_daily_user_day ON tool_usage_daily(user_id, day);
+CREATE INDEX IF NOT EXISTS idx_tool_usage_daily_tool ON tool_usage_daily(tool_name, day);
@@ -0,0 +1,26 @@
+-- Public status page: component health probes and admin incident notes
+
+-- One row per component per probe run. Uptime is derived from these samples.
+CREATE TABLE IF NOT EXISTS status_probes (
+    component TEXT NOT NULL,
+    status TEXT NOT NULL,
+    checked_at TEXT NOT NULL DEFAULT (datetime('now')),
+    PRIMARY KEY (component, checked_at)
+);
+
+CREATE INDEX IF NOT EXISTS idx_status_probes_checked_at ON status_probes(checked_at);
+
+-- Incident notes shown on the status page until resolved.
+CREATE TABLE IF NOT EXISTS status_incidents (
+    id TEXT PRIMARY KEY,
+    title TEXT NOT NULL,
+    message TEXT NOT NULL DEFAULT '',
+    severity TEXT NOT NULL DEFAULT 'minor',
+    component TEXT,
+    created_by TEXT NOT NULL,
+    created_at TEXT NOT NULL DEFAULT (datetime('now')),
+    updated_at TEXT NOT NULL DEFAULT (datetime('now')),
+    resolved_at TEXT
+);
+
+CREATE INDEX IF NOT EXISTS idx_status_incidents_resolved_at ON status_incidents(resolved_at);
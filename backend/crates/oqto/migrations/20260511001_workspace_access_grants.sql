@@ -0,0 +1,23 @@
+-- Delegated, time-boxed access from one user to a directory in another
+-- user's workspace. Enforced with POSIX ACLs applied by oqto-usermgr.
+
+CREATE TABLE IF NOT EXISTS workspace_access_grants (
+    id TEXT PRIMARY KEY,
+    owner_id TEXT NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    grantee_id TEXT NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    session_id TEXT,
+    path TEXT NOT NULL,
+    mode TEXT NOT NULL DEFAULT 'read_only',
+    status TEXT NOT NULL DEFAULT 'pending',
+    reason TEXT,
+    duration_minutes INTEGER NOT NULL,
+    requested_at TEXT NOT NULL DEFAULT (datetime('now')),
+    decided_at TEXT,
+    expires_at TEXT,
+    revoked_at TEXT,
+    revoked_by TEXT
+);
+
+CREATE INDEX IF NOT EXISTS idx_workspace_access_grants_owner ON workspace_access_grants(owner_id, status);
+CREATE INDEX IF NOT EXISTS idx_workspace_access_grants_grantee ON workspace_access_grants(grantee_id, status);
+CREATE INDEX IF NOT EXISTS idx_workspace_access_grants_expires ON workspace_access_grants(status, expires_at);
@@ -0,0 +1,21 @@
+-- Forensic bundles collected by a user's runner after an agent harness
+-- process exited abnormally. The bundle itself stays on the runner; this
+-- table attaches its summary to the chat session it belongs to.
+
+CREATE TABLE IF NOT EXISTS session_crash_bundles (
+    id TEXT NOT NULL,
+    user_id TEXT NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    session_id TEXT NOT NULL,
+    pi_session_id TEXT,
+    harness TEXT NOT NULL,
+    exit_code INTEGER,
+    signal INTEGER,
+    has_core_dump INTEGER NOT NULL DEFAULT 0,
+    last_stderr TEXT,
+    collected_at TEXT NOT NULL,
+    recorded_at TEXT NOT NULL DEFAULT (datetime('now')),
+    PRIMARY KEY (user_id, id)
+);
+
+CREATE INDEX IF NOT EXISTS idx_session_crash_bundles_session ON session_crash_bundles(session_id);
+CREATE INDEX IF NOT EXISTS idx_session_crash_bundles_collected ON session_crash_bundles(collected_at);
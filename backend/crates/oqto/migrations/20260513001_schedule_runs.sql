@@ -0,0 +1,37 @@
+-- Run history for skdlr schedules. Runs are reported by the scheduler;
+-- runs that were due while the server was down are recorded as skipped.
+
+CREATE TABLE IF NOT EXISTS schedule_runs (
+    id TEXT NOT NULL,
+    user_id TEXT NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    schedule_name TEXT NOT NULL,
+    status TEXT NOT NULL,
+    scheduled_for TEXT,
+    started_at TEXT,
+    ended_at TEXT,
+    exit_code INTEGER,
+    log_path TEXT,
+    cost_usd REAL,
+    error TEXT,
+    -- started_at, or scheduled_for for skipped runs; runs are ordered by it.
+    run_at TEXT NOT NULL,
+    PRIMARY KEY (user_id, id)
+);
+
+CREATE INDEX IF NOT EXISTS idx_schedule_runs_schedule ON schedule_runs(user_id, schedule_name, run_at);
+CREATE INDEX IF NOT EXISTS idx_schedule_runs_run_at ON schedule_runs(run_at);
+
+-- Last seen expression of each schedule, used to find missed runs.
+CREATE TABLE IF NOT EXISTS schedule_definitions (
+    user_id TEXT NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    name TEXT NOT NULL,
+    schedule TEXT NOT NULL,
+    updated_at TEXT NOT NULL,
+    PRIMARY KEY (user_id, name)
+);
+
+-- Last time the server was known to be up.
+CREATE TABLE IF NOT EXISTS scheduler_heartbeat (
+    id INTEGER PRIMARY KEY CHECK (id = 1),
+    beat_at TEXT NOT NULL
+);
@@ -0,0 +1,23 @@
+-- Memories an agent suggested for promotion into the user's mmry store.
+-- Nothing reaches mmry until the user accepts the suggestion.
+
+CREATE TABLE IF NOT EXISTS memory_suggestions (
+    id TEXT PRIMARY KEY,
+    user_id TEXT NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    session_id TEXT NOT NULL,
+    workspace_path TEXT NOT NULL,
+    message_id TEXT,
+    content TEXT NOT NULL,
+    tags TEXT NOT NULL DEFAULT '[]',
+    category TEXT,
+    importance INTEGER,
+    memory_type TEXT,
+    reason TEXT,
+    status TEXT NOT NULL DEFAULT 'pending',
+    memory_id TEXT,
+    created_at TEXT NOT NULL DEFAULT (datetime('now')),
+    reviewed_at TEXT
+);
+
+CREATE INDEX IF NOT EXISTS idx_memory_suggestions_user ON memory_suggestions(user_id, status, created_at);
+CREATE INDEX IF NOT EXISTS idx_memory_suggestions_session ON memory_suggestions(session_id, status);
@@ -0,0 +1,4 @@
+-- Per-session secret the fileserver checks capability tokens against.
+-- Regenerated whenever the session's local processes are (re)started.
+
+ALTER TABLE sessions ADD COLUMN fileserver_token_secret TEXT;
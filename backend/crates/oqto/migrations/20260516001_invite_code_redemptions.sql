@@ -0,0 +1,12 @@
+-- Usage history of invite codes: one row per successful registration.
+-- `username` is a snapshot so the history survives deleting the user.
+
+CREATE TABLE IF NOT EXISTS invite_code_redemptions (
+    id TEXT PRIMARY KEY,
+    invite_code_id TEXT NOT NULL REFERENCES invite_codes(id) ON DELETE CASCADE,
+    user_id TEXT REFERENCES users(id) ON DELETE SET NULL,
+    username TEXT NOT NULL,
+    redeemed_at TEXT NOT NULL DEFAULT (datetime('now'))
+);
+
+CREATE INDEX IF NOT EXISTS idx_invite_code_redemptions_code ON invite_code_redemptions(invite_code_id, redeemed_at);
@@ -0,0 +1,5 @@
+-- Per-member capability grants for shared workspaces (JSON object with
+-- chat_read, chat_write, terminal_view, terminal_control, file_read and
+-- file_write flags). NULL means the defaults of the member's role apply.
+
+ALTER TABLE shared_workspace_members ADD COLUMN permissions TEXT;
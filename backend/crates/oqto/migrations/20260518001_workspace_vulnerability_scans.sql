@@ -0,0 +1,39 @@
+-- Dependency vulnerability scans of workspaces, one row per workspace.
+-- Re-scans update the row in place so its id stays stable for the API.
+
+CREATE TABLE IF NOT EXISTS workspace_vuln_scans (
+    id TEXT PRIMARY KEY,
+    workspace_path TEXT NOT NULL UNIQUE,
+    -- User whose file change or request triggered the latest scan.
+    user_id TEXT REFERENCES users(id) ON DELETE SET NULL,
+    status TEXT NOT NULL DEFAULT 'running',
+    triggered_by TEXT NOT NULL,
+    -- JSON array of per-scanner outcomes.
+    scanners TEXT NOT NULL DEFAULT '[]',
+    error TEXT,
+    started_at TEXT NOT NULL DEFAULT (datetime('now')),
+    finished_at TEXT
+);
+
+CREATE INDEX IF NOT EXISTS idx_workspace_vuln_scans_user ON workspace_vuln_scans(user_id);
+
+-- Findings of the latest scan of a workspace.
+CREATE TABLE IF NOT EXISTS workspace_vulnerabilities (
+    scan_id TEXT NOT NULL REFERENCES workspace_vuln_scans(id) ON DELETE CASCADE,
+    ecosystem TEXT NOT NULL DEFAULT '',
+    package TEXT NOT NULL,
+    version TEXT NOT NULL DEFAULT '',
+    advisory_id TEXT NOT NULL,
+    scanners TEXT NOT NULL DEFAULT '[]',
+    aliases TEXT NOT NULL DEFAULT '[]',
+    severity TEXT NOT NULL DEFAULT 'unknown',
+    cvss_score REAL,
+    summary TEXT,
+    fixed_version TEXT,
+    source_path TEXT,
+    -- Kept across re-scans; findings are new when first seen by a scan.
+    first_seen_at TEXT NOT NULL DEFAULT (datetime('now')),
+    PRIMARY KEY (scan_id, ecosystem, package, version, advisory_id)
+);
+
+CREATE INDEX IF NOT EXISTS idx_workspace_vulnerabilities_severity ON workspace_vulnerabilities(scan_id, severity);
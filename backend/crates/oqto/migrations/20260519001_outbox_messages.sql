@@ -0,0 +1,29 @@
+-- Outbound messages (email, Slack) composed by agents. Agents only stage
+-- them here; the backend sends a message after the user approves it.
+
+CREATE TABLE IF NOT EXISTS outbox_messages (
+    id TEXT PRIMARY KEY,
+    user_id TEXT NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    session_id TEXT NOT NULL,
+    channel TEXT NOT NULL,
+    -- JSON arrays: email addresses, or the Slack channel.
+    recipients TEXT NOT NULL DEFAULT '[]',
+    cc TEXT NOT NULL DEFAULT '[]',
+    subject TEXT,
+    body TEXT NOT NULL,
+    reason TEXT,
+    status TEXT NOT NULL DEFAULT 'pending',
+    -- The agent's draft as JSON when the reviewer edited it before sending.
+    original TEXT,
+    reviewed_by TEXT,
+    reviewed_at TEXT,
+    review_note TEXT,
+    sent_at TEXT,
+    -- Message-ID (email) or message timestamp (Slack) of the sent message.
+    provider_message_id TEXT,
+    error TEXT,
+    created_at TEXT NOT NULL DEFAULT (datetime('now'))
+);
+
+CREATE INDEX IF NOT EXISTS idx_outbox_messages_user ON outbox_messages(user_id, status, created_at);
+CREATE INDEX IF NOT EXISTS idx_outbox_messages_session ON outbox_messages(session_id, status);
@@ -0,0 +1,17 @@
+-- Named bookmarks at points in a session's timeline. Bookmarks in shared
+-- workspace sessions record the workspace so every member who can read the
+-- session sees them; bookmarks in personal sessions are private.
+
+CREATE TABLE IF NOT EXISTS session_bookmarks (
+    id TEXT PRIMARY KEY,
+    session_id TEXT NOT NULL,
+    user_id TEXT NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    workspace_id TEXT,
+    label TEXT NOT NULL,
+    -- Message the bookmark points at, and its position in the transcript.
+    message_id TEXT NOT NULL,
+    message_index INTEGER,
+    created_at TEXT NOT NULL DEFAULT (datetime('now'))
+);
+
+CREATE INDEX IF NOT EXISTS idx_session_bookmarks_session ON session_bookmarks(session_id, message_index);
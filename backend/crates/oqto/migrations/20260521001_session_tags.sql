@@ -0,0 +1,18 @@
+-- Tags assigned to sessions by classifying their first prompt: a topic
+-- (coding, research, writing, ops) plus detected languages and frameworks.
+-- `user_id` is who sent the classified prompt.
+
+CREATE TABLE IF NOT EXISTS session_tags (
+    session_id TEXT NOT NULL,
+    user_id TEXT NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    tag TEXT NOT NULL,
+    -- topic, language or framework
+    kind TEXT NOT NULL,
+    -- model or rules
+    source TEXT NOT NULL,
+    created_at TEXT NOT NULL DEFAULT (datetime('now')),
+    PRIMARY KEY (session_id, tag)
+);
+
+CREATE INDEX IF NOT EXISTS idx_session_tags_user ON session_tags(user_id, tag);
+CREATE INDEX IF NOT EXISTS idx_session_tags_tag ON session_tags(tag, created_at);
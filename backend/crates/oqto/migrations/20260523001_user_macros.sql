@@ -0,0 +1,15 @@
+-- User-defined macros: named sequences of agent commands with parameters,
+-- run against a session with POST /api/macros/{id}/run. Parameters and
+-- steps are stored as JSON.
+
+CREATE TABLE IF NOT EXISTS user_macros (
+    id TEXT PRIMARY KEY,
+    user_id TEXT NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    name TEXT NOT NULL,
+    description TEXT,
+    params TEXT NOT NULL DEFAULT '[]',
+    steps TEXT NOT NULL DEFAULT '[]',
+    created_at TEXT NOT NULL DEFAULT (datetime('now')),
+    updated_at TEXT NOT NULL DEFAULT (datetime('now')),
+    UNIQUE (user_id, name)
+);
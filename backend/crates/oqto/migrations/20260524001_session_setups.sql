@@ -0,0 +1,16 @@
+-- Per-session setup beyond the container: model, extra environment
+-- variables, and the session a clone was made from. `cloned_from` is not a
+-- foreign key so provenance survives deleting the source session.
+
+CREATE TABLE IF NOT EXISTS session_setups (
+    session_id TEXT PRIMARY KEY NOT NULL REFERENCES sessions(id) ON DELETE CASCADE,
+    cloned_from TEXT,
+    -- How a clone got its workspace: shared, copy or clean.
+    workspace TEXT,
+    provider TEXT,
+    model TEXT,
+    env TEXT NOT NULL DEFAULT '{}',
+    created_at TEXT NOT NULL DEFAULT (datetime('now'))
+);
+
+CREATE INDEX IF NOT EXISTS idx_session_setups_cloned_from ON session_setups(cloned_from);
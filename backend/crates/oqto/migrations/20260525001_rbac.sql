@@ -0,0 +1,37 @@
+-- Role-based access control. Admins have every permission; other users get
+-- permissions through the roles assigned to them. The built-in `operator`
+-- role covers day-to-day administration (sessions, templates, invites,
+-- settings) without user or provider management.
+
+CREATE TABLE IF NOT EXISTS roles (
+    name TEXT PRIMARY KEY NOT NULL,
+    description TEXT,
+    -- Built-in roles can be edited but not deleted.
+    builtin INTEGER NOT NULL DEFAULT 0,
+    created_at TEXT NOT NULL DEFAULT (datetime('now'))
+);
+
+CREATE TABLE IF NOT EXISTS role_permissions (
+    role TEXT NOT NULL REFERENCES roles(name) ON DELETE CASCADE,
+    permission TEXT NOT NULL,
+    PRIMARY KEY (role, permission)
+);
+
+CREATE TABLE IF NOT EXISTS user_roles (
+    user_id TEXT NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    role TEXT NOT NULL REFERENCES roles(name) ON DELETE CASCADE,
+    granted_by TEXT,
+    created_at TEXT NOT NULL DEFAULT (datetime('now')),
+    PRIMARY KEY (user_id, role)
+);
+
+CREATE INDEX IF NOT EXISTS idx_user_roles_role ON user_roles(role);
+
+INSERT OR IGNORE INTO roles (name, description, builtin)
+VALUES ('operator', 'Manage sessions, templates, invites and settings', 1);
+
+INSERT OR IGNORE INTO role_permissions (role, permission) VALUES
+    ('operator', 'sessions.manage'),
+    ('operator', 'templates.manage'),
+    ('operator', 'invites.manage'),
+    ('operator', 'settings.edit');
@@ -0,0 +1,16 @@
+-- Sessions shared with other users. A shared session keeps running on its
+-- owner's runner; each row lets one more user watch it (`read`) or also
+-- prompt it (`write`).
+
+CREATE TABLE IF NOT EXISTS session_shares (
+    session_id TEXT NOT NULL,
+    owner_user_id TEXT NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    user_id TEXT NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    access TEXT NOT NULL CHECK (access IN ('read', 'write')),
+    created_at TEXT NOT NULL DEFAULT (datetime('now')),
+    updated_at TEXT NOT NULL DEFAULT (datetime('now')),
+    PRIMARY KEY (session_id, user_id)
+);
+
+CREATE INDEX IF NOT EXISTS idx_session_shares_user ON session_shares(user_id);
+CREATE INDEX IF NOT EXISTS idx_session_shares_owner ON session_shares(owner_user_id);
@@ -0,0 +1,4 @@
+-- Structured diagnostics of why a session last stopped (JSON): exit code,
+-- signal, failure category and a hint for users.
+
+ALTER TABLE sessions ADD COLUMN exit_info TEXT;
@@ -0,0 +1,10 @@
+-- In-progress prompts shared between a user's devices. `state` is the
+-- draft's CRDT state as JSON (see `prompt_drafts::DraftState`).
+
+CREATE TABLE IF NOT EXISTS prompt_drafts (
+    user_id TEXT NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    session_id TEXT NOT NULL,
+    state TEXT NOT NULL,
+    updated_at TEXT NOT NULL DEFAULT (datetime('now')),
+    PRIMARY KEY (user_id, session_id)
+);
@@ -0,0 +1,13 @@
+-- HTTPS credentials (usually personal access tokens) used when pushing a
+-- workspace repository through `POST /workspaces/{id}/git/push`. `host` is
+-- the remote's host, with the port when it is not the default.
+
+CREATE TABLE IF NOT EXISTS git_credentials (
+    user_id TEXT NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    host TEXT NOT NULL,
+    username TEXT NOT NULL,
+    token TEXT NOT NULL,
+    created_at TEXT NOT NULL DEFAULT (datetime('now')),
+    updated_at TEXT NOT NULL DEFAULT (datetime('now')),
+    PRIMARY KEY (user_id, host)
+);
@@ -0,0 +1,30 @@
+-- Agent schedules run by the backend itself: on each cron occurrence a new
+-- session is started in `workspace_path`, sent `prompt` (or the prompt
+-- template `template`), and closed once the agent is idle again. Runs are
+-- recorded in schedule_runs under the schedule's name.
+
+CREATE TABLE IF NOT EXISTS agent_schedules (
+    id TEXT PRIMARY KEY,
+    user_id TEXT NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    name TEXT NOT NULL,
+    cron TEXT NOT NULL,
+    workspace_path TEXT NOT NULL,
+    prompt TEXT NOT NULL,
+    template TEXT,
+    harness TEXT,
+    provider TEXT,
+    model TEXT,
+    timeout_secs INTEGER,
+    enabled INTEGER NOT NULL DEFAULT 1,
+    -- Next cron occurrence; NULL while disabled.
+    next_run_at TEXT,
+    last_run_at TEXT,
+    created_at TEXT NOT NULL DEFAULT (datetime('now')),
+    updated_at TEXT NOT NULL DEFAULT (datetime('now')),
+    UNIQUE (user_id, name)
+);
+
+CREATE INDEX IF NOT EXISTS idx_agent_schedules_due ON agent_schedules(enabled, next_run_at);
+
+-- Session an agent schedule run used.
+ALTER TABLE schedule_runs ADD COLUMN session_id TEXT;
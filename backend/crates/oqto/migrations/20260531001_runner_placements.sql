@@ -0,0 +1,13 @@
+-- Which runner host a session was placed on when [[runners]] federates
+-- sessions across several hosts. Sessions without a row run on the owner's
+-- own runner. Failover rewrites runner_id when a host goes down.
+
+CREATE TABLE IF NOT EXISTS runner_placements (
+    session_id TEXT PRIMARY KEY,
+    user_id TEXT NOT NULL,
+    runner_id TEXT NOT NULL,
+    created_at TEXT NOT NULL DEFAULT (datetime('now')),
+    updated_at TEXT NOT NULL DEFAULT (datetime('now'))
+);
+
+CREATE INDEX IF NOT EXISTS idx_runner_placements_runner ON runner_placements(runner_id);
@@ -0,0 +1,50 @@
+-- Inbound triggers: an external event (a webhook call to
+-- POST /api/triggers/{token}, or an email fetched by the IMAP poller) starts
+-- a session in `workspace_path` with `prompt` rendered from the event. Only
+-- the SHA-256 of the token is stored. The agent's final answer can be
+-- delivered back to `reply_webhook_url` and/or by email to the sender.
+
+CREATE TABLE IF NOT EXISTS agent_triggers (
+    id TEXT PRIMARY KEY,
+    user_id TEXT NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    name TEXT NOT NULL,
+    token_hash TEXT NOT NULL UNIQUE,
+    -- First characters of the token, to tell tokens apart in the UI.
+    token_prefix TEXT NOT NULL,
+    workspace_path TEXT NOT NULL,
+    prompt TEXT NOT NULL,
+    template TEXT,
+    harness TEXT,
+    provider TEXT,
+    model TEXT,
+    timeout_secs INTEGER,
+    -- JSON array of addresses, @domains and IP addresses.
+    allowed_senders TEXT NOT NULL DEFAULT '[]',
+    reply_webhook_url TEXT,
+    reply_email INTEGER NOT NULL DEFAULT 0,
+    enabled INTEGER NOT NULL DEFAULT 1,
+    last_fired_at TEXT,
+    created_at TEXT NOT NULL DEFAULT (datetime('now')),
+    updated_at TEXT NOT NULL DEFAULT (datetime('now')),
+    UNIQUE (user_id, name)
+);
+
+CREATE TABLE IF NOT EXISTS trigger_runs (
+    id TEXT PRIMARY KEY,
+    trigger_id TEXT NOT NULL REFERENCES agent_triggers(id) ON DELETE CASCADE,
+    -- webhook | email
+    source TEXT NOT NULL,
+    sender TEXT,
+    subject TEXT,
+    -- queued | running | succeeded | failed
+    status TEXT NOT NULL,
+    session_id TEXT NOT NULL,
+    error TEXT,
+    -- Result delivery: NULL when nothing was delivered, else 'sent' or the error.
+    delivery TEXT,
+    created_at TEXT NOT NULL,
+    started_at TEXT,
+    finished_at TEXT
+);
+
+CREATE INDEX IF NOT EXISTS idx_trigger_runs_trigger ON trigger_runs(trigger_id, created_at);
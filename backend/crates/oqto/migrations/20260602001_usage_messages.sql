@@ -0,0 +1,27 @@
+-- Per-message LLM usage for attributing spend to sessions and models.
+-- EAVS meters spend per virtual key (one per user); the session and model
+-- split comes from the usage agents report on assistant messages.
+
+-- One row per assistant message. The same message can be seen by several
+-- WebSocket connections subscribed to one session.
+CREATE TABLE IF NOT EXISTS usage_messages (
+    session_id TEXT NOT NULL,
+    message_id TEXT NOT NULL,
+    user_id TEXT NOT NULL,
+    model TEXT NOT NULL,
+    provider TEXT NOT NULL DEFAULT '',
+    input_tokens INTEGER NOT NULL DEFAULT 0,
+    output_tokens INTEGER NOT NULL DEFAULT 0,
+    cache_read_tokens INTEGER NOT NULL DEFAULT 0,
+    cache_write_tokens INTEGER NOT NULL DEFAULT 0,
+    cost_usd REAL NOT NULL DEFAULT 0,
+    -- UTC day (YYYY-MM-DD) the message finished on.
+    day TEXT NOT NULL,
+    -- Unix milliseconds.
+    created_at INTEGER NOT NULL,
+    PRIMARY KEY (session_id, message_id)
+);
+
+CREATE INDEX IF NOT EXISTS idx_usage_messages_user_day ON usage_messages(user_id, day);
+CREATE INDEX IF NOT EXISTS idx_usage_messages_user_session ON usage_messages(user_id, session_id);
+CREATE INDEX IF NOT EXISTS idx_usage_messages_day ON usage_messages(day);
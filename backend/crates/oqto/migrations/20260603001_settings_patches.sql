@@ -0,0 +1,19 @@
+-- History of JSON Patch (RFC 6902) edits to settings documents.
+-- `config_path` identifies the document (global or workspace-scoped file);
+-- versions are content hashes of the file before and after the edit.
+-- Values of x-sensitive settings are stored redacted.
+
+CREATE TABLE IF NOT EXISTS settings_patches (
+    id TEXT PRIMARY KEY,
+    app TEXT NOT NULL,
+    config_path TEXT NOT NULL,
+    user_id TEXT NOT NULL,
+    base_version TEXT NOT NULL,
+    version TEXT NOT NULL,
+    -- JSON array of patch operations.
+    operations TEXT NOT NULL,
+    created_at TEXT NOT NULL DEFAULT (datetime('now'))
+);
+
+CREATE INDEX IF NOT EXISTS idx_settings_patches_path ON settings_patches(config_path, created_at);
+CREATE INDEX IF NOT EXISTS idx_settings_patches_user ON settings_patches(user_id, created_at);
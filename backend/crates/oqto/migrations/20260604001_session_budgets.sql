@@ -0,0 +1,16 @@
+-- Per-session spend budgets, checked against the attributed spend in
+-- usage_messages. Sessions only get a row once a budget threshold is
+-- crossed or an admin sets a session-specific budget.
+
+CREATE TABLE IF NOT EXISTS session_budgets (
+    session_id TEXT PRIMARY KEY,
+    user_id TEXT NOT NULL,
+    -- Overrides `[budget] session_limit_usd` for this session.
+    limit_usd REAL,
+    -- Highest threshold crossed: 'ok', 'warning' or 'exceeded'. Each
+    -- crossing is announced once.
+    level TEXT NOT NULL DEFAULT 'ok',
+    updated_at TEXT NOT NULL DEFAULT (datetime('now'))
+);
+
+CREATE INDEX IF NOT EXISTS idx_session_budgets_level ON session_budgets(level);
@@ -0,0 +1,11 @@
+-- Container resource limits. Sessions keep the limits their container was
+-- created with (JSON: cpu_shares, memory_mb, pids_limit, gpus); admins can
+-- set per-user defaults that override `[container.resources]`.
+
+ALTER TABLE sessions ADD COLUMN resource_limits TEXT;
+
+CREATE TABLE IF NOT EXISTS user_resource_limits (
+    user_id TEXT PRIMARY KEY REFERENCES users(id) ON DELETE CASCADE,
+    limits TEXT NOT NULL,
+    updated_at TEXT NOT NULL DEFAULT (datetime('now'))
+);
@@ -0,0 +1,25 @@
+-- Open self-service registrations (`[registration] open = true`). Each row is
+-- a user who registered without an invite code: the client IP and time feed
+-- the registration rate limits, `quarantined` holds the user to the
+-- quarantine quota until the email address is verified, and `status` tracks
+-- admin approval.
+
+CREATE TABLE IF NOT EXISTS registrations (
+    user_id TEXT PRIMARY KEY REFERENCES users(id) ON DELETE CASCADE,
+    email TEXT NOT NULL,
+    client_ip TEXT,
+    status TEXT NOT NULL CHECK (status IN ('pending_approval', 'active', 'rejected')),
+    quarantined INTEGER NOT NULL DEFAULT 0,
+    verification_token_hash TEXT,
+    verification_expires_at TEXT,
+    verified_at TEXT,
+    reviewed_by TEXT,
+    reviewed_at TEXT,
+    created_at TEXT NOT NULL DEFAULT (datetime('now'))
+);
+
+CREATE INDEX IF NOT EXISTS idx_registrations_created ON registrations(created_at);
+CREATE INDEX IF NOT EXISTS idx_registrations_ip ON registrations(client_ip, created_at);
+CREATE INDEX IF NOT EXISTS idx_registrations_status ON registrations(status);
+CREATE UNIQUE INDEX IF NOT EXISTS idx_registrations_token
+    ON registrations(verification_token_hash);
@@ -0,0 +1,5 @@
+-- Idle sessions can be hibernated instead of stopped. The JSON snapshot
+-- (hibernated_at, container checkpoint name, agent sessions to resume) is
+-- restored and cleared by the next resume.
+
+ALTER TABLE sessions ADD COLUMN hibernation TEXT;
@@ -0,0 +1,43 @@
+-- Session drafts: sessions configured ahead of time and started later, by
+-- hand or once `start_at` has passed. A draft holds no ports or container,
+-- so it does not count against session limits until it is started; it then
+-- points at the session it became (`session_id`) and, if it had a prompt,
+-- the agent session the prompt went to (`chat_session_id`).
+
+CREATE TABLE IF NOT EXISTS session_drafts (
+    id TEXT PRIMARY KEY,
+    user_id TEXT NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    name TEXT,
+    workspace_path TEXT,
+    image TEXT,
+    agent TEXT,
+    provider TEXT,
+    model TEXT,
+    env TEXT NOT NULL DEFAULT '{}',
+    mounts TEXT NOT NULL DEFAULT '[]',
+    prompt TEXT,
+    start_at TEXT,
+    status TEXT NOT NULL DEFAULT 'draft'
+        CHECK (status IN ('draft', 'starting', 'started', 'failed')),
+    session_id TEXT,
+    chat_session_id TEXT,
+    error TEXT,
+    created_at TEXT NOT NULL DEFAULT (datetime('now')),
+    updated_at TEXT NOT NULL DEFAULT (datetime('now'))
+);
+
+CREATE INDEX IF NOT EXISTS idx_session_drafts_user ON session_drafts(user_id);
+CREATE INDEX IF NOT EXISTS idx_session_drafts_due ON session_drafts(status, start_at);
+
+-- Users a draft is shared with for review (read-only).
+CREATE TABLE IF NOT EXISTS session_draft_shares (
+    draft_id TEXT NOT NULL REFERENCES session_drafts(id) ON DELETE CASCADE,
+    user_id TEXT NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    created_at TEXT NOT NULL DEFAULT (datetime('now')),
+    PRIMARY KEY (draft_id, user_id)
+);
+
+CREATE INDEX IF NOT EXISTS idx_session_draft_shares_user ON session_draft_shares(user_id);
+
+-- Extra directories mounted into a session's container (JSON list).
+ALTER TABLE session_setups ADD COLUMN mounts TEXT;
@@ -0,0 +1,28 @@
+-- Reactions, flags and notes on agent responses. Each annotation records
+-- the provider and model that produced the message so feedback can be
+-- aggregated per model. Annotations in shared workspace sessions record the
+-- workspace so every member who can read the session sees them.
+
+CREATE TABLE IF NOT EXISTS message_annotations (
+    id TEXT PRIMARY KEY,
+    session_id TEXT NOT NULL,
+    message_id TEXT NOT NULL,
+    user_id TEXT NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    workspace_id TEXT,
+    kind TEXT NOT NULL CHECK (kind IN ('reaction', 'flag', 'note')),
+    -- `up`/`down` for reactions, the flag name, or the note text.
+    value TEXT NOT NULL,
+    provider TEXT,
+    model TEXT,
+    created_at TEXT NOT NULL DEFAULT (datetime('now')),
+    updated_at TEXT NOT NULL DEFAULT (datetime('now'))
+);
+
+CREATE INDEX IF NOT EXISTS idx_message_annotations_session ON message_annotations(session_id, message_id);
+CREATE INDEX IF NOT EXISTS idx_message_annotations_model ON message_annotations(provider, model);
+
+-- One reaction per user and message; each flag once per user and message.
+CREATE UNIQUE INDEX IF NOT EXISTS idx_message_annotations_reaction
+    ON message_annotations(session_id, message_id, user_id) WHERE kind = 'reaction';
+CREATE UNIQUE INDEX IF NOT EXISTS idx_message_annotations_flag
+    ON message_annotations(session_id, message_id, user_id, value) WHERE kind = 'flag';
@@ -0,0 +1,23 @@
+-- Admin impersonation of users. An admin asks to act as a user; the user
+-- approves or denies, or policy allows a break-glass start with a
+-- justification. `expires_at` is the consent deadline of a pending request
+-- and the end of an active one. Audit events recorded while impersonating
+-- carry the row's ID.
+
+CREATE TABLE IF NOT EXISTS impersonations (
+    id TEXT PRIMARY KEY,
+    admin_id TEXT NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    user_id TEXT NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    reason TEXT NOT NULL,
+    break_glass INTEGER NOT NULL DEFAULT 0,
+    status TEXT NOT NULL CHECK (status IN ('pending', 'active', 'denied', 'ended')),
+    duration_minutes INTEGER NOT NULL,
+    requested_at TEXT NOT NULL DEFAULT (datetime('now')),
+    started_at TEXT,
+    expires_at TEXT NOT NULL,
+    ended_at TEXT,
+    ended_by TEXT
+);
+
+CREATE INDEX IF NOT EXISTS idx_impersonations_user ON impersonations(user_id, requested_at);
+CREATE INDEX IF NOT EXISTS idx_impersonations_admin ON impersonations(admin_id, requested_at);
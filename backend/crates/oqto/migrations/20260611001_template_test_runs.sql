@@ -0,0 +1,22 @@
+-- Smoke-check runs of workspace templates. `version` is a hash of the
+-- template's files at the time of the run; `git_commit` the last commit of
+-- the templates repository touching it, when that is a git checkout. Only
+-- the most recent runs of each template are kept.
+
+CREATE TABLE IF NOT EXISTS template_test_runs (
+    id TEXT PRIMARY KEY,
+    template TEXT NOT NULL,
+    version TEXT NOT NULL,
+    git_commit TEXT,
+    command TEXT NOT NULL,
+    status TEXT NOT NULL CHECK (status IN ('passed', 'failed', 'timed_out', 'error')),
+    exit_code INTEGER,
+    output TEXT NOT NULL DEFAULT '',
+    output_truncated INTEGER NOT NULL DEFAULT 0,
+    duration_ms INTEGER NOT NULL DEFAULT 0,
+    started_by TEXT REFERENCES users(id) ON DELETE SET NULL,
+    created_at TEXT NOT NULL DEFAULT (datetime('now'))
+);
+
+CREATE INDEX IF NOT EXISTS idx_template_test_runs_template
+    ON template_test_runs(template, created_at);
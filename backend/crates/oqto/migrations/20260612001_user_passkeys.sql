@@ -0,0 +1,15 @@
+-- Passkeys (WebAuthn credentials) users sign in with. `credential` is the
+-- serialized credential with its public key and signature counter;
+-- `credential_id` its base64url ID, which identifies it across all users.
+
+CREATE TABLE IF NOT EXISTS user_passkeys (
+    id TEXT PRIMARY KEY,
+    user_id TEXT NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    name TEXT NOT NULL,
+    credential_id TEXT NOT NULL UNIQUE,
+    credential TEXT NOT NULL,
+    created_at TEXT NOT NULL DEFAULT (datetime('now')),
+    last_used_at TEXT
+);
+
+CREATE INDEX IF NOT EXISTS idx_user_passkeys_user ON user_passkeys(user_id);
@@ -0,0 +1,17 @@
+-- Messages, or single parts of messages, that the owner of a session keeps
+-- to themselves. Everyone else who can read the session (workspace members,
+-- share participants) gets a placeholder instead. `part_id` is empty when
+-- the whole message is private.
+
+CREATE TABLE IF NOT EXISTS private_messages (
+    id TEXT PRIMARY KEY,
+    session_id TEXT NOT NULL,
+    message_id TEXT NOT NULL,
+    part_id TEXT NOT NULL DEFAULT '',
+    owner_user_id TEXT NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    workspace_id TEXT,
+    created_at TEXT NOT NULL DEFAULT (datetime('now')),
+    UNIQUE (session_id, message_id, part_id)
+);
+
+CREATE INDEX IF NOT EXISTS idx_private_messages_session ON private_messages(session_id);
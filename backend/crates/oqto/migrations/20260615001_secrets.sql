@@ -0,0 +1,19 @@
+-- Secrets agents need (API keys, service account files), stored as sealed
+-- boxes under the server key and injected into sessions when they start.
+-- `workspace_path` limits a secret to sessions in that project ('' = all of
+-- the user's sessions). `kind` is 'env' (`name` is the variable) or 'file'
+-- (`name` is the file name under $OQTO_SECRETS_DIR).
+
+CREATE TABLE IF NOT EXISTS secrets (
+    id TEXT PRIMARY KEY,
+    user_id TEXT NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    workspace_path TEXT NOT NULL DEFAULT '',
+    kind TEXT NOT NULL CHECK (kind IN ('env', 'file')),
+    name TEXT NOT NULL,
+    sealed_value TEXT NOT NULL,
+    created_at TEXT NOT NULL DEFAULT (datetime('now')),
+    updated_at TEXT NOT NULL DEFAULT (datetime('now')),
+    UNIQUE (user_id, workspace_path, kind, name)
+);
+
+CREATE INDEX IF NOT EXISTS idx_secrets_user ON secrets(user_id);
@@ -0,0 +1,3 @@
+-- Sandbox profile a container session selected; its egress rules are
+-- attached to the container before it starts.
+ALTER TABLE session_setups ADD COLUMN sandbox_profile TEXT;
@@ -0,0 +1,297 @@
+//! Pre-aggregated admin dashboard (`GET /api/admin/overview`).
+//!
+//! The landing page needs numbers that are each expensive to compute
+//! (listing every session, asking EAVS for every key, statting volumes), so
+//! they are rolled up into one [`AdminOverview`] that is cached for
+//! [`OVERVIEW_TTL`] and shared by concurrent requests.
+//!
+//! EAVS only reports cumulative spend per key. Spend today is the growth of
+//! that total since the first rollup of the UTC day; after a restart it
+//! counts from the first rollup (see [`SpendToday::since`]).
+
+use std::ffi::CString;
+use std::os::unix::ffi::OsStrExt;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use anyhow::Result;
+use chrono::{DateTime, NaiveDate, Utc};
+use serde::Serialize;
+use tokio::sync::Mutex;
+use tracing::debug;
+
+use crate::eavs::EavsClient;
+use crate::observability::metrics::{CallTotals, metrics, spend_by_user};
+use crate::session::{RuntimeMode, SessionService, SessionStatus};
+use crate::status::{Incident, IncidentListQuery, StatusService};
+use crate::ws::hub::WsHub;
+
+/// How long a rollup is served before it is recomputed.
+pub const OVERVIEW_TTL: Duration = Duration::from_secs(30);
+
+/// Incidents included in the overview.
+const RECENT_INCIDENTS: i64 = 5;
+
+/// Everything the admin landing page shows.
+#[derive(Debug, Clone, Serialize)]
+pub struct AdminOverview {
+    /// When this rollup was computed.
+    pub generated_at: DateTime<Utc>,
+    /// Users with a running session.
+    pub active_users: usize,
+    /// Users with an open WebSocket.
+    pub connected_users: usize,
+    pub running_sessions: RunningSessions,
+    /// None when EAVS is not configured or has not answered yet.
+    pub spend_today: Option<SpendToday>,
+    /// Failed runner RPCs and hstry writes since the server started.
+    pub errors: ErrorRates,
+    pub disk: Vec<DiskHeadroom>,
+    /// Newest incidents, resolved ones included.
+    pub recent_incidents: Vec<Incident>,
+}
+
+/// Running sessions by runtime mode.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct RunningSessions {
+    pub total: usize,
+    pub container: usize,
+    pub local: usize,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SpendToday {
+    pub usd: f64,
+    /// Start of the measurement: midnight UTC, or the first rollup of the
+    /// day if the server started later.
+    pub since: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ErrorRates {
+    pub runner_rpcs: u64,
+    pub runner_rpc_errors: u64,
+    /// Share of runner RPCs that failed (0 without calls).
+    pub runner_rpc_error_rate: f64,
+    pub hstry_writes: u64,
+    pub hstry_write_errors: u64,
+    pub hstry_write_error_rate: f64,
+}
+
+impl From<CallTotals> for ErrorRates {
+    fn from(totals: CallTotals) -> Self {
+        let rate = |errors: u64, calls: u64| {
+            if calls == 0 {
+                0.0
+            } else {
+                errors as f64 / calls as f64
+            }
+        };
+        Self {
+            runner_rpcs: totals.runner_rpcs,
+            runner_rpc_errors: totals.runner_rpc_errors,
+            runner_rpc_error_rate: rate(totals.runner_rpc_errors, totals.runner_rpcs),
+            hstry_writes: totals.hstry_writes,
+            hstry_write_errors: totals.hstry_write_errors,
+            hstry_write_error_rate: rate(totals.hstry_write_errors, totals.hstry_writes),
+        }
+    }
+}
+
+/// Free space on a volume the server writes to.
+#[derive(Debug, Clone, Serialize)]
+pub struct DiskHeadroom {
+    /// What lives there, e.g. `data` or `user_data`.
+    pub label: String,
+    pub path: String,
+    pub total_bytes: u64,
+    /// Bytes available to unprivileged users.
+    pub available_bytes: u64,
+    pub used_percent: f64,
+}
+
+/// Cumulative EAVS spend at the start of a day.
+#[derive(Debug, Clone)]
+struct SpendBaseline {
+    day: NaiveDate,
+    total_usd: f64,
+    since: DateTime<Utc>,
+}
+
+/// Computes and caches [`AdminOverview`] rollups.
+pub struct AdminOverviewService {
+    /// Volumes to report headroom for, by label.
+    volumes: Vec<(String, PathBuf)>,
+    eavs: Option<Arc<EavsClient>>,
+    cache: Mutex<Option<(Instant, Arc<AdminOverview>)>>,
+    baseline: Mutex<Option<SpendBaseline>>,
+}
+
+impl AdminOverviewService {
+    pub fn new(volumes: Vec<(String, PathBuf)>, eavs: Option<Arc<EavsClient>>) -> Self {
+        Self {
+            volumes,
+            eavs,
+            cache: Mutex::new(None),
+            baseline: Mutex::new(None),
+        }
+    }
+
+    /// The current rollup, recomputed when older than [`OVERVIEW_TTL`].
+    pub async fn overview(
+        &self,
+        sessions: &SessionService,
+        hub: &WsHub,
+        status: Option<&StatusService>,
+    ) -> Result<Arc<AdminOverview>> {
+        // Held while computing so concurrent requests wait for one rollup.
+        let mut cache = self.cache.lock().await;
+        if let Some((computed, overview)) = cache.as_ref()
+            && computed.elapsed() < OVERVIEW_TTL
+        {
+            return Ok(Arc::clone(overview));
+        }
+        let overview = Arc::new(self.compute(sessions, hub, status).await?);
+        *cache = Some((Instant::now(), Arc::clone(&overview)));
+        Ok(overview)
+    }
+
+    async fn compute(
+        &self,
+        sessions: &SessionService,
+        hub: &WsHub,
+        status: Option<&StatusService>,
+    ) -> Result<AdminOverview> {
+        let sessions = sessions.list_sessions().await?;
+        let running: Vec<_> = sessions
+            .iter()
+            .filter(|s| s.status == SessionStatus::Running)
+            .collect();
+        let mut running_sessions = RunningSessions {
+            total: running.len(),
+            ..Default::default()
+        };
+        for session in &running {
+            match session.runtime_mode {
+                RuntimeMode::Container => running_sessions.container += 1,
+                RuntimeMode::Local => running_sessions.local += 1,
+            }
+        }
+        let active_users = running
+            .iter()
+            .map(|s| s.user_id.as_str())
+            .collect::<std::collections::HashSet<_>>()
+            .len();
+
+        let recent_incidents = match status {
+            Some(status) => {
+                status
+                    .repository()
+                    .list_incidents(&IncidentListQuery {
+                        include_resolved: true,
+                        limit: Some(RECENT_INCIDENTS),
+                    })
+                    .await?
+            }
+            None => Vec::new(),
+        };
+
+        let volumes = self.volumes.clone();
+        let disk = tokio::task::spawn_blocking(move || {
+            volumes
+                .iter()
+                .filter_map(|(label, path)| headroom(label, path))
+                .collect()
+        })
+        .await?;
+
+        Ok(AdminOverview {
+            generated_at: Utc::now(),
+            active_users,
+            connected_users: hub.connected_user_count(),
+            running_sessions,
+            spend_today: self.spend_today().await,
+            errors: metrics().call_totals().into(),
+            disk,
+            recent_incidents,
+        })
+    }
+
+    async fn spend_today(&self) -> Option<SpendToday> {
+        let eavs = self.eavs.as_ref()?;
+        let total_usd: f64 = match eavs.list_keys().await {
+            Ok(keys) => spend_by_user(&keys).iter().map(|(_, spend)| spend).sum(),
+            Err(e) => {
+                debug!("Failed to list EAVS keys for admin overview: {e}");
+                return None;
+            }
+        };
+        let now = Utc::now();
+        let mut baseline = self.baseline.lock().await;
+        let baseline = match baseline.as_mut() {
+            Some(baseline) if baseline.day == now.date_naive() => baseline,
+            _ => baseline.insert(SpendBaseline {
+                day: now.date_naive(),
+                total_usd,
+                since: now,
+            }),
+        };
+        Some(SpendToday {
+            // Keys deleted during the day lower the total; never go negative.
+            usd: (total_usd - baseline.total_usd).max(0.0),
+            since: baseline.since,
+        })
+    }
+}
+
+/// Size and free space of the filesystem holding `path`.
+fn headroom(label: &str, path: &Path) -> Option<DiskHeadroom> {
+    let c_path = CString::new(path.as_os_str().as_bytes()).ok()?;
+    // SAFETY: statvfs only writes into the struct, which is valid and
+    // zeroed; the path is NUL-terminated.
+    let mut stat: libc::statvfs = unsafe { std::mem::zeroed() };
+    if unsafe { libc::statvfs(c_path.as_ptr(), &mut stat) } != 0 {
+        debug!("statvfs failed for {}", path.display());
+        return None;
+    }
+    let block = stat.f_frsize as u64;
+    let total_bytes = stat.f_blocks as u64 * block;
+    let available_bytes = stat.f_bavail as u64 * block;
+    let free_bytes = stat.f_bfree as u64 * block;
+    let used_percent = if total_bytes == 0 {
+        0.0
+    } else {
+        (total_bytes - free_bytes) as f64 / total_bytes as f64 * 100.0
+    };
+    Some(DiskHeadroom {
+        label: label.to_string(),
+        path: path.display().to_string(),
+        total_bytes,
+        available_bytes,
+        used_percent,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_headroom_and_error_rates() {
+        let dir = tempfile::tempdir().unwrap();
+        let disk = headroom("data", dir.path()).unwrap();
+        assert!(disk.total_bytes >= disk.available_bytes);
+        assert!((0.0..=100.0).contains(&disk.used_percent));
+        assert!(headroom("missing", Path::new("/nonexistent/oqto")).is_none());
+
+        let rates = ErrorRates::from(CallTotals {
+            runner_rpcs: 200,
+            runner_rpc_errors: 5,
+            hstry_writes: 0,
+            hstry_write_errors: 0,
+        });
+        assert_eq!(rates.runner_rpc_error_rate, 0.025);
+        assert_eq!(rates.hstry_write_error_rate, 0.0);
+    }
+}
@@ -2,13 +2,28 @@
 //!
 //! This module provides a lightweight wrapper to start/stop per-session
 //! agent-browser daemons via the CLI binary.
-
-use std::time::Duration;
+//!
+//! Two things keep repeated browsing cheap:
+//! - Each user has a persistent profile (cookies, localStorage) that every
+//!   daemon started for one of their sessions loads at launch and writes
+//!   back while running and on exit, so agents stay logged in to sites.
+//! - A small pool of pre-launched headless daemons is kept warm. A session
+//!   that needs a browser adopts one (the daemon moves its socket to the
+//!   session and loads the user's profile) instead of cold-starting
+//!   Chromium. Pooled daemons nobody claims are reaped after an idle timeout
+//!   and the pool refills on the next claim.
+
+use std::collections::VecDeque;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 
 use anyhow::{Context, Result};
+use dashmap::DashMap;
 use log::debug;
 use serde::{Deserialize, Serialize};
 use tokio::process::Command;
+use tokio::sync::Mutex;
 use tokio::time::timeout;
 
 #[cfg(unix)]
@@ -32,6 +47,16 @@ pub struct AgentBrowserConfig {
     pub executable_path: Option<String>,
     /// Extensions to load (paths).
     pub extensions: Vec<String>,
+    /// Keep a persistent browser profile (cookies, localStorage) per user.
+    pub persist_profiles: bool,
+    /// Directory for per-user profiles
+    /// (default: `$XDG_DATA_HOME/oqto/agent-browser/profiles`).
+    pub profile_dir: Option<String>,
+    /// Pre-launched headless daemons kept ready for new sessions (0 disables
+    /// pooling). Ignored when extensions are configured.
+    pub pool_size: usize,
+    /// Seconds an unclaimed pooled daemon stays up before it is reaped.
+    pub pool_idle_timeout_seconds: u64,
 }
 
 impl Default for AgentBrowserConfig {
@@ -44,20 +69,48 @@ impl Default for AgentBrowserConfig {
             stream_port_range: 10000,
             executable_path: None,
             extensions: Vec::new(),
+            persist_profiles: true,
+            profile_dir: None,
+            pool_size: 1,
+            pool_idle_timeout_seconds: 600,
         }
     }
 }
 
+/// How often unclaimed pooled daemons are checked for the idle timeout.
+const POOL_REAP_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Pre-launched daemons waiting to be adopted, oldest first.
+#[derive(Debug, Default)]
+struct BrowserPool {
+    idle: VecDeque<PooledBrowser>,
+    next_id: u64,
+    filling: bool,
+}
+
+#[derive(Debug)]
+struct PooledBrowser {
+    session: String,
+    ready_since: Instant,
+}
+
 /// Manager for agent-browser per-session daemons.
 #[derive(Debug, Clone)]
 pub struct AgentBrowserManager {
     config: AgentBrowserConfig,
+    /// Owning user of each browser session, for profile lookup.
+    owners: Arc<DashMap<String, String>>,
+    pool: Arc<Mutex<BrowserPool>>,
 }
 
 impl AgentBrowserManager {
     /// Create a new manager.
     pub fn new(config: AgentBrowserConfig) -> Self {
-        Self { config }
+        Self {
+            config,
+            owners: Arc::new(DashMap::new()),
+            pool: Arc::new(Mutex::new(BrowserPool::default())),
+        }
     }
 
     /// Whether agent-browser integration is enabled.
@@ -119,15 +172,199 @@ impl AgentBrowserManager {
         }
     }
 
-    /// Ensure the daemon is running for the session.
-    pub async fn ensure_session(&self, session_id: &str) -> Result<()> {
+    /// Ensure the daemon is running for the session. `user_id` selects the
+    /// persistent profile the browser uses.
+    pub async fn ensure_session(&self, session_id: &str, user_id: Option<&str>) -> Result<()> {
         if !self.config.enabled {
             return Ok(());
         }
 
+        if let Some(user_id) = user_id {
+            self.owners
+                .insert(session_id.to_string(), user_id.to_string());
+        }
+        self.ensure_daemon(session_id).await
+    }
+
+    /// Start the session's daemon unless it is running, adopting a pooled
+    /// one when available.
+    async fn ensure_daemon(&self, session_id: &str) -> Result<()> {
+        if daemon_running(session_id) {
+            return Ok(());
+        }
+
+        let adopted = self.adopt_pooled(session_id).await;
+        if self.pool_enabled() {
+            let manager = self.clone();
+            tokio::spawn(async move { manager.fill_pool().await });
+        }
+        if adopted {
+            return Ok(());
+        }
         self.run_command(session_id, &["open", "about:blank"]).await
     }
 
+    fn pool_enabled(&self) -> bool {
+        self.config.enabled && self.config.pool_size > 0 && self.config.extensions.is_empty()
+    }
+
+    /// Hand a pooled daemon over to `session_id`. Returns false when none
+    /// could be adopted.
+    async fn adopt_pooled(&self, session_id: &str) -> bool {
+        if !self.pool_enabled() {
+            return false;
+        }
+
+        loop {
+            let Some(pooled) = self.pool.lock().await.idle.pop_front() else {
+                return false;
+            };
+            if !daemon_running(&pooled.session) {
+                continue;
+            }
+
+            let stream_port = match self.compute_stream_port(session_id) {
+                Ok(port) => port.to_string(),
+                Err(err) => {
+                    log::warn!("Not adopting pooled agent-browser: {}", err);
+                    self.stop_pooled(&pooled.session).await;
+                    return false;
+                }
+            };
+            let mut args = vec!["adopt", session_id, "--stream-port", &stream_port];
+            let profile = self
+                .profile_state_path(session_id)
+                .map(|p| p.to_string_lossy().into_owned());
+            if let Some(ref profile) = profile {
+                args.extend(["--profile-state", profile.as_str()]);
+            }
+
+            match self.run_browserd(&pooled.session, &args, true).await {
+                Ok(()) => {
+                    debug!(
+                        "Session {} adopted pooled agent-browser {}",
+                        session_id, pooled.session
+                    );
+                    return true;
+                }
+                Err(err) => {
+                    log::warn!(
+                        "Failed to adopt pooled agent-browser {} for {}: {}",
+                        pooled.session,
+                        session_id,
+                        err
+                    );
+                    self.stop_pooled(&pooled.session).await;
+                }
+            }
+        }
+    }
+
+    /// Launch pooled daemons until the pool is full.
+    async fn fill_pool(&self) {
+        let missing = {
+            let mut pool = self.pool.lock().await;
+            if pool.filling {
+                return;
+            }
+            pool.filling = true;
+            self.config.pool_size.saturating_sub(pool.idle.len())
+        };
+
+        for _ in 0..missing {
+            let session = {
+                let mut pool = self.pool.lock().await;
+                pool.next_id += 1;
+                format!("abp-{}", pool.next_id)
+            };
+            match self
+                .run_browserd(&session, &["open", "about:blank"], true)
+                .await
+            {
+                Ok(()) => self.pool.lock().await.idle.push_back(PooledBrowser {
+                    session,
+                    ready_since: Instant::now(),
+                }),
+                Err(err) => {
+                    log::warn!("Failed to pre-launch agent-browser {}: {}", session, err);
+                    self.stop_pooled(&session).await;
+                    break;
+                }
+            }
+        }
+
+        self.pool.lock().await.filling = false;
+    }
+
+    /// Stop pooled daemons that have waited longer than the idle timeout.
+    async fn reap_idle_pool(&self) {
+        let idle_timeout = Duration::from_secs(self.config.pool_idle_timeout_seconds);
+        let expired = {
+            let mut pool = self.pool.lock().await;
+            let (expired, keep): (Vec<_>, VecDeque<_>) = pool
+                .idle
+                .drain(..)
+                .partition(|p| p.ready_since.elapsed() >= idle_timeout);
+            pool.idle = keep;
+            expired
+        };
+        for pooled in expired {
+            debug!("Reaping idle pooled agent-browser {}", pooled.session);
+            self.stop_pooled(&pooled.session).await;
+        }
+    }
+
+    async fn stop_pooled(&self, session: &str) {
+        if let Err(err) = self.run_browserd(session, &["close"], true).await {
+            debug!("Failed to stop pooled agent-browser {}: {}", session, err);
+        }
+        let _ = std::fs::remove_dir_all(agent_browser_session_dir(session, None));
+    }
+
+    /// Pre-launch the browser pool and reap idle pooled daemons. Does
+    /// nothing when pooling is disabled.
+    pub fn start_pool_task(&self) -> Option<tokio::task::JoinHandle<()>> {
+        if !self.pool_enabled() {
+            return None;
+        }
+        let manager = self.clone();
+        Some(tokio::spawn(async move {
+            manager.fill_pool().await;
+            let mut interval = tokio::time::interval(POOL_REAP_INTERVAL);
+            interval.tick().await;
+            loop {
+                interval.tick().await;
+                manager.reap_idle_pool().await;
+            }
+        }))
+    }
+
+    /// Profile state file of the user owning `session_id`.
+    fn profile_state_path(&self, session_id: &str) -> Option<PathBuf> {
+        if !self.config.persist_profiles || !self.config.extensions.is_empty() {
+            return None;
+        }
+        let user_id = self.owners.get(session_id)?.clone();
+        let base = self
+            .config
+            .profile_dir
+            .as_ref()
+            .map(|dir| PathBuf::from(shellexpand::tilde(dir).as_ref()))
+            .unwrap_or_else(agent_browser_profile_dir);
+        let dir = base.join(profile_dir_name(&user_id));
+        if let Err(err) = std::fs::create_dir_all(&dir) {
+            log::warn!(
+                "Failed to create agent-browser profile dir {}: {}",
+                dir.display(),
+                err
+            );
+            return None;
+        }
+        #[cfg(unix)]
+        let _ = std::fs::set_permissions(&dir, std::fs::Permissions::from_mode(0o700));
+        Some(dir.join("storage-state.json"))
+    }
+
     /// Navigate to a URL, launching the daemon if not already running.
     ///
     /// First ensures the daemon is running (opens about:blank), then navigates
@@ -139,8 +376,7 @@ impl AgentBrowserManager {
         }
 
         // Ensure daemon is running first (always succeeds for valid sessions)
-        self.run_command(session_id, &["open", "about:blank"])
-            .await?;
+        self.ensure_daemon(session_id).await?;
 
         // Navigate to the requested URL -- best-effort, don't fail the launch
         if let Err(e) = self.run_command(session_id, &["open", url]).await {
@@ -173,7 +409,9 @@ impl AgentBrowserManager {
             return Ok(());
         }
 
-        self.run_command(session_id, &["close"]).await
+        let result = self.run_command(session_id, &["close"]).await;
+        self.owners.remove(session_id);
+        result
     }
 
     /// Navigate back in the browser history.
@@ -218,15 +456,29 @@ impl AgentBrowserManager {
     }
 
     async fn run_command(&self, session_id: &str, args: &[&str]) -> Result<()> {
+        self.run_browserd(session_id, args, false).await
+    }
+
+    /// Run a CLI command against `session_id`. Pooled daemons are always
+    /// headless and get their stream port and profile when adopted.
+    async fn run_browserd(&self, session_id: &str, args: &[&str], pooled: bool) -> Result<()> {
         let mut cmd = Command::new(&self.config.binary);
         cmd.arg("--session").arg(session_id);
 
-        if self.config.headed {
-            cmd.arg("--headed");
-        }
+        if pooled {
+            cmd.env("AGENT_BROWSER_POOLED", "1");
+        } else {
+            if self.config.headed {
+                cmd.arg("--headed");
+            }
+
+            let stream_port = self.compute_stream_port(session_id)?;
+            cmd.env("AGENT_BROWSER_STREAM_PORT", stream_port.to_string());
 
-        let stream_port = self.compute_stream_port(session_id)?;
-        cmd.env("AGENT_BROWSER_STREAM_PORT", stream_port.to_string());
+            if let Some(profile) = self.profile_state_path(session_id) {
+                cmd.env("AGENT_BROWSER_PROFILE_STATE", profile);
+            }
+        }
 
         let socket_dir = agent_browser_session_dir(session_id, None);
         if let Err(err) = std::fs::create_dir_all(&socket_dir) {
@@ -332,6 +584,50 @@ pub fn agent_browser_base_dir() -> std::path::PathBuf {
     std::env::temp_dir().join("oqto").join("agent-browser")
 }
 
+/// Default base directory for per-user browser profiles.
+fn agent_browser_profile_dir() -> PathBuf {
+    dirs::data_dir()
+        .unwrap_or_else(std::env::temp_dir)
+        .join("oqto")
+        .join("agent-browser")
+        .join("profiles")
+}
+
+/// Filesystem-safe directory name for a user's profile.
+fn profile_dir_name(user_id: &str) -> String {
+    user_id
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '-' || c == '_' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
+
+/// Whether a daemon for `session_id` is alive (per its PID file).
+fn daemon_running(session_id: &str) -> bool {
+    let pid_file = agent_browser_session_dir(session_id, None).join(format!("{session_id}.pid"));
+    let Some(pid) = std::fs::read_to_string(pid_file)
+        .ok()
+        .and_then(|raw| raw.trim().parse::<i32>().ok())
+    else {
+        return false;
+    };
+    #[cfg(unix)]
+    {
+        // SAFETY: signal 0 only checks that the process exists.
+        unsafe { libc::kill(pid, 0) == 0 }
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = pid;
+        true
+    }
+}
+
 /// Compute a short, deterministic agent-browser session name from a chat session ID.
 ///
 /// Unix socket paths are limited (about 103 bytes). Chat session IDs (UUIDs)
@@ -368,3 +664,33 @@ fn read_stream_port_file(session_id: &str) -> Option<u16> {
     }
     trimmed.parse::<u16>().ok()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_profile_dir_name_is_path_safe() {
+        assert_eq!(profile_dir_name("user_ab12-x"), "user_ab12-x");
+        assert_eq!(
+            profile_dir_name("../alice@example.com"),
+            "___alice_example_com"
+        );
+    }
+
+    #[test]
+    fn test_pool_disabled_with_extensions() {
+        let config = AgentBrowserConfig {
+            enabled: true,
+            ..Default::default()
+        };
+        assert!(AgentBrowserManager::new(config.clone()).pool_enabled());
+        assert!(
+            !AgentBrowserManager::new(AgentBrowserConfig {
+                extensions: vec!["/opt/ext".to_string()],
+                ..config
+            })
+            .pool_enabled()
+        );
+    }
+}
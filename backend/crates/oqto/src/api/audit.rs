@@ -19,18 +19,22 @@ pub async fn audit_middleware(
     let start = Instant::now();
     let method = req.method().to_string();
     let path = req.uri().path().to_string();
-    let user_id = req
-        .extensions()
-        .get::<CurrentUser>()
-        .map(|user| user.id().to_string());
+    let user = req.extensions().get::<CurrentUser>().cloned();
 
     let response = next.run(req).await;
 
-    if let (Some(logger), Some(user_id)) = (state.audit_logger.as_ref(), user_id) {
+    if let (Some(logger), Some(user)) = (state.audit_logger.as_ref(), user) {
         let status = response.status().as_u16();
         let duration_ms = start.elapsed().as_millis();
         logger
-            .log_http(&user_id, &method, &path, status, duration_ms)
+            .log_http(
+                user.id(),
+                user.impersonator(),
+                &method,
+                &path,
+                status,
+                duration_ms,
+            )
             .await;
     }
 
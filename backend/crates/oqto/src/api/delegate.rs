@@ -77,6 +77,8 @@ pub struct SessionInfo {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub error_message: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
+    pub exit_info: Option<crate::session::ExitInfo>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub source: Option<String>,
 }
 
@@ -167,6 +169,7 @@ pub async fn get_status(
         started_at: session.started_at,
         last_activity_at: session.last_activity_at,
         error_message: session.error_message,
+        exit_info: session.exit_info,
         source: None,
     }))
 }
@@ -250,6 +253,7 @@ pub async fn list_sessions(
             started_at: s.started_at,
             last_activity_at: s.last_activity_at,
             error_message: s.error_message,
+            exit_info: s.exit_info,
             source: None,
         })
         .collect();
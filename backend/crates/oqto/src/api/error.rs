@@ -31,6 +31,9 @@ pub enum ApiError {
     #[error("Service unavailable: {0}")]
     ServiceUnavailable(String),
 
+    #[error("Too many requests: {0}")]
+    TooManyRequests(String),
+
     #[error("Internal server error: {0}")]
     Internal(String),
 
@@ -64,6 +67,10 @@ impl ApiError {
         Self::ServiceUnavailable(msg.into())
     }
 
+    pub fn too_many_requests(msg: impl Into<String>) -> Self {
+        Self::TooManyRequests(msg.into())
+    }
+
     pub fn internal(msg: impl Into<String>) -> Self {
         Self::Internal(msg.into())
     }
@@ -80,6 +87,7 @@ impl ApiError {
             Self::Forbidden(_) => StatusCode::FORBIDDEN,
             Self::Conflict(_) => StatusCode::CONFLICT,
             Self::ServiceUnavailable(_) => StatusCode::SERVICE_UNAVAILABLE,
+            Self::TooManyRequests(_) => StatusCode::TOO_MANY_REQUESTS,
             Self::Internal(_) => StatusCode::INTERNAL_SERVER_ERROR,
             Self::BadGateway(_) => StatusCode::BAD_GATEWAY,
         }
@@ -93,6 +101,7 @@ impl ApiError {
             Self::Forbidden(_) => "FORBIDDEN",
             Self::Conflict(_) => "CONFLICT",
             Self::ServiceUnavailable(_) => "SERVICE_UNAVAILABLE",
+            Self::TooManyRequests(_) => "TOO_MANY_REQUESTS",
             Self::Internal(_) => "INTERNAL_ERROR",
             Self::BadGateway(_) => "BAD_GATEWAY",
         }
@@ -230,6 +239,7 @@ impl From<crate::auth::AuthError> for ApiError {
                 ApiError::Unauthorized(format!("Invalid token: {}", msg))
             }
             AuthError::TokenExpired => ApiError::Unauthorized("Token has expired".to_string()),
+            AuthError::TokenRevoked => ApiError::Unauthorized("Token has been revoked".to_string()),
             AuthError::InvalidCredentials => {
                 ApiError::Unauthorized("Invalid credentials".to_string())
             }
@@ -8,20 +8,30 @@ use axum::{
     Json,
     extract::{Path, Query, State},
     http::StatusCode,
-    response::sse::{Event, KeepAlive, Sse},
+    response::{
+        IntoResponse,
+        sse::{Event, KeepAlive, Sse},
+    },
 };
+use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use tokio::sync::Mutex;
 use tokio_stream::{StreamExt, wrappers::IntervalStream};
 use tracing::{error, info, instrument, warn};
 
-use crate::auth::RequireAdmin;
+use crate::audit::{AuditQuery, events_to_csv};
+use crate::auth::{Access, Permission, RequireAdmin, RevocationScope};
+use crate::container::ResourceLimits;
+use crate::crash_bundles::{CrashBundleQuery, CrashBundleRecord, CrashBundleService};
 use crate::observability::{CpuTimes, HostMetrics, read_host_metrics};
-use crate::session::{Session, SessionContainerStats};
+use crate::session::{Session, SessionContainerStats, UserResourceLimits};
 use crate::user::{
     CreateUserRequest, UpdateUserRequest, UserInfo as DbUserInfo, UserListQuery, UserStats,
 };
 
+use crate::runner::router::{ExecutionTarget, resolve_runner_for_target};
+use oqto_runner::client::RunnerClient;
+
 use crate::api::error::{ApiError, ApiResult};
 use crate::api::state::AppState;
 
@@ -67,6 +77,22 @@ pub async fn get_admin_stats(
     }))
 }
 
+/// Dashboard overview in one payload (admin only), served from a rollup
+/// refreshed at most every 30 seconds.
+pub async fn get_admin_overview(
+    State(state): State<AppState>,
+    RequireAdmin(_user): RequireAdmin,
+) -> ApiResult<Json<crate::admin_overview::AdminOverview>> {
+    let service = state
+        .admin_overview
+        .as_ref()
+        .ok_or_else(|| ApiError::service_unavailable("Admin overview is not available"))?;
+    let overview = service
+        .overview(&state.sessions, &state.ws_hub, state.status.as_deref())
+        .await?;
+    Ok(Json(overview.as_ref().clone()))
+}
+
 /// Get event bus statistics (admin only).
 pub async fn get_bus_stats(
     State(state): State<AppState>,
@@ -75,6 +101,182 @@ pub async fn get_bus_stats(
     Ok(Json(state.bus.stats()))
 }
 
+/// SIEM export pipeline health (admin only). Reports `enabled: false` when
+/// export is not configured.
+pub async fn get_siem_health(
+    State(state): State<AppState>,
+    RequireAdmin(_user): RequireAdmin,
+) -> ApiResult<Json<crate::siem::ExportHealth>> {
+    Ok(Json(
+        state
+            .siem
+            .as_ref()
+            .map(|exporter| exporter.health())
+            .unwrap_or_default(),
+    ))
+}
+
+/// Default page size of the audit log query.
+const AUDIT_DEFAULT_LIMIT: usize = 100;
+/// Largest page of a JSON audit log query.
+const AUDIT_MAX_LIMIT: usize = 1000;
+/// Most events in one CSV export.
+const AUDIT_MAX_EXPORT: usize = 100_000;
+
+/// Query parameters of the audit log API.
+#[derive(Debug, Deserialize)]
+pub struct AuditLogQuery {
+    pub user_id: Option<String>,
+    /// Event type, e.g. `http_request`, `ws_command`, `auth_login_failed`.
+    pub event: Option<String>,
+    pub session_id: Option<String>,
+    /// Events recorded during one impersonation.
+    pub impersonation_id: Option<String>,
+    /// RFC 3339 timestamp; events at or after it.
+    pub since: Option<String>,
+    /// RFC 3339 timestamp; events before it.
+    pub until: Option<String>,
+    pub limit: Option<usize>,
+    #[serde(default)]
+    pub offset: usize,
+    /// `json` (default) or `csv`.
+    pub format: Option<String>,
+}
+
+fn parse_audit_time(name: &str, value: Option<&str>) -> ApiResult<Option<DateTime<Utc>>> {
+    value
+        .filter(|v| !v.trim().is_empty())
+        .map(|v| {
+            DateTime::parse_from_rfc3339(v.trim())
+                .map(|t| t.with_timezone(&Utc))
+                .map_err(|_| ApiError::bad_request(format!("{name} must be an RFC 3339 timestamp")))
+        })
+        .transpose()
+}
+
+/// Query the audit log (admin only): filter by user, event type, session and
+/// time range, newest first. `format=csv` exports the matching events.
+#[instrument(skip(state, admin))]
+pub async fn admin_query_audit_log(
+    State(state): State<AppState>,
+    RequireAdmin(admin): RequireAdmin,
+    Query(query): Query<AuditLogQuery>,
+) -> ApiResult<axum::response::Response> {
+    use axum::http::header;
+
+    let logger = state
+        .audit_logger
+        .as_ref()
+        .ok_or_else(|| ApiError::not_found("Audit logging is disabled"))?;
+    let csv = match query.format.as_deref().unwrap_or("json") {
+        "json" => false,
+        "csv" => true,
+        other => {
+            return Err(ApiError::bad_request(format!(
+                "Unsupported format '{other}' (use json or csv)"
+            )));
+        }
+    };
+    let limit = match (csv, query.limit) {
+        (false, limit) => limit
+            .unwrap_or(AUDIT_DEFAULT_LIMIT)
+            .clamp(1, AUDIT_MAX_LIMIT),
+        (true, limit) => limit.unwrap_or(AUDIT_MAX_EXPORT).clamp(1, AUDIT_MAX_EXPORT),
+    };
+    let filter = |value: Option<String>| value.filter(|v| !v.trim().is_empty());
+    let audit_query = AuditQuery {
+        user_id: filter(query.user_id),
+        event: filter(query.event),
+        session_id: filter(query.session_id),
+        impersonation_id: filter(query.impersonation_id),
+        since: parse_audit_time("since", query.since.as_deref())?,
+        until: parse_audit_time("until", query.until.as_deref())?,
+        limit,
+        offset: query.offset,
+    };
+    let page = logger.query(&audit_query).await?;
+    info!(
+        admin_id = %admin.id(),
+        total = page.total,
+        returned = page.events.len(),
+        csv,
+        "Admin queried audit log"
+    );
+
+    if csv {
+        return Ok((
+            [
+                (header::CONTENT_TYPE, "text/csv; charset=utf-8"),
+                (
+                    header::CONTENT_DISPOSITION,
+                    "attachment; filename=\"audit-log.csv\"",
+                ),
+            ],
+            events_to_csv(&page.events),
+        )
+            .into_response());
+    }
+    Ok(Json(page).into_response())
+}
+
+/// Reload config.toml now and apply the settings that can change while
+/// running (admin only). Reports what was applied and what needs a restart.
+pub async fn admin_reload_config(
+    State(state): State<AppState>,
+    RequireAdmin(_user): RequireAdmin,
+) -> ApiResult<Json<crate::config_reload::ReloadReport>> {
+    let reloader = state
+        .config_reloader
+        .as_ref()
+        .ok_or_else(|| ApiError::service_unavailable("Config reload is not available"))?;
+    let report = reloader
+        .reload()
+        .await
+        .map_err(|e| ApiError::bad_request(format!("Config not reloaded: {e:#}")))?;
+    Ok(Json(report))
+}
+
+/// Interactive activity and background slot usage (admin only).
+pub async fn get_priority_lanes(
+    State(state): State<AppState>,
+    RequireAdmin(_user): RequireAdmin,
+) -> ApiResult<Json<crate::priority_lanes::LaneStats>> {
+    let lanes = state
+        .priority_lanes
+        .as_ref()
+        .ok_or_else(|| ApiError::not_found("Priority lanes are not enabled"))?;
+    Ok(Json(lanes.snapshot()))
+}
+
+/// Session resume pacing: free tokens, queued resumes and totals (admin
+/// only).
+pub async fn get_reconnect_stats(
+    State(state): State<AppState>,
+    RequireAdmin(_user): RequireAdmin,
+) -> ApiResult<Json<crate::ws::reconnect::ReconnectStats>> {
+    Ok(Json(state.reconnect.snapshot()))
+}
+
+/// Health and load of federated runner hosts (admin only).
+pub async fn get_runner_hosts(
+    State(state): State<AppState>,
+    RequireAdmin(_user): RequireAdmin,
+) -> ApiResult<Json<Vec<crate::runner::federation::RunnerHostStatus>>> {
+    let pool = state
+        .runner_pool
+        .as_ref()
+        .ok_or_else(|| ApiError::not_found("No runner hosts are configured"))?;
+    Ok(Json(pool.statuses()))
+}
+
+/// Bytes each user moved through the HTTP proxies since startup (admin only).
+pub async fn get_proxy_transfers(
+    State(state): State<AppState>,
+    RequireAdmin(_user): RequireAdmin,
+) -> ApiResult<Json<Vec<crate::api::proxy::UserTransfer>>> {
+    Ok(Json(state.proxy_transfers.snapshot()))
+}
+
 #[derive(Debug, Deserialize)]
 pub struct PublishBusEventRequest {
     pub scope: crate::bus::BusScope,
@@ -130,24 +332,26 @@ pub struct AdminMetricsSnapshot {
     pub error: Option<String>,
 }
 
-/// List all sessions (admin only).
-#[instrument(skip(state, _user))]
+/// List all sessions (admin or `sessions.manage`).
+#[instrument(skip(state, access))]
 pub async fn admin_list_sessions(
     State(state): State<AppState>,
-    RequireAdmin(_user): RequireAdmin,
+    access: Access,
 ) -> ApiResult<Json<Vec<Session>>> {
+    access.require(Permission::SessionsManage)?;
     let sessions = state.sessions.list_sessions().await?;
     info!(count = sessions.len(), "Admin listed all sessions");
     Ok(Json(sessions))
 }
 
-/// Force stop a session (admin only).
-#[instrument(skip(state, _user))]
+/// Force stop a session (admin or `sessions.manage`).
+#[instrument(skip(state, access))]
 pub async fn admin_force_stop_session(
     State(state): State<AppState>,
-    RequireAdmin(_user): RequireAdmin,
+    access: Access,
     Path(session_id): Path<String>,
 ) -> ApiResult<StatusCode> {
+    access.require(Permission::SessionsManage)?;
     // Uses centralized From<anyhow::Error> conversion
     state.sessions.stop_session(&session_id).await?;
 
@@ -155,22 +359,139 @@ pub async fn admin_force_stop_session(
     Ok(StatusCode::NO_CONTENT)
 }
 
+/// Override a container session's resource limits (admin or `sessions.manage`).
+#[instrument(skip(state, access))]
+pub async fn admin_set_session_resource_limits(
+    State(state): State<AppState>,
+    access: Access,
+    Path(session_id): Path<String>,
+    Json(limits): Json<ResourceLimits>,
+) -> ApiResult<Json<Session>> {
+    access.require(Permission::SessionsManage)?;
+    let session = state
+        .sessions
+        .set_session_resource_limits(&session_id, &limits)
+        .await?;
+
+    info!(session_id = %session_id, ?limits, "Admin set session resource limits");
+    Ok(Json(session))
+}
+
 #[derive(Debug, Serialize)]
 pub struct LocalCleanupResponse {
     pub cleared: usize,
 }
 
-/// Clean up orphan local session processes (admin only).
-#[instrument(skip(state, _user))]
+/// Clean up orphan local session processes (admin or `sessions.manage`).
+#[instrument(skip(state, access))]
 pub async fn admin_cleanup_local_sessions(
     State(state): State<AppState>,
-    RequireAdmin(_user): RequireAdmin,
+    access: Access,
 ) -> ApiResult<Json<LocalCleanupResponse>> {
+    access.require(Permission::SessionsManage)?;
     let cleared = state.sessions.cleanup_local_orphans().await?;
     info!(cleared, "Admin cleaned up local sessions");
     Ok(Json(LocalCleanupResponse { cleared }))
 }
 
+fn crash_bundle_service(state: &AppState) -> ApiResult<&CrashBundleService> {
+    state
+        .crash_bundles
+        .as_deref()
+        .ok_or_else(|| ApiError::not_found("Crash bundles are not available"))
+}
+
+async fn personal_runner(state: &AppState, user_id: &str) -> ApiResult<RunnerClient> {
+    resolve_runner_for_target(state, user_id, &ExecutionTarget::Personal)
+        .await
+        .map_err(|e| ApiError::internal(format!("Failed to resolve runner: {e:#}")))?
+        .ok_or_else(|| ApiError::service_unavailable("No runner available for user"))
+}
+
+/// List harness crash bundles attached to sessions (admin only).
+///
+/// With `user_id` set, the user's runner is asked for bundles first so
+/// crashes that happened while nobody was connected are attached too.
+#[instrument(skip(state, _user))]
+pub async fn admin_list_crash_bundles(
+    State(state): State<AppState>,
+    RequireAdmin(_user): RequireAdmin,
+    Query(query): Query<CrashBundleQuery>,
+) -> ApiResult<Json<Vec<CrashBundleRecord>>> {
+    let service = crash_bundle_service(&state)?;
+    if let Some(user_id) = &query.user_id {
+        match personal_runner(&state, user_id).await {
+            Ok(runner) => {
+                if let Err(e) = service
+                    .sync(&runner, user_id, query.session_id.as_deref())
+                    .await
+                {
+                    warn!(user_id = %user_id, "Crash bundle sync failed: {:#}", e);
+                }
+            }
+            Err(e) => warn!(user_id = %user_id, "Crash bundle sync skipped: {}", e),
+        }
+    }
+    Ok(Json(service.repository().list(&query).await?))
+}
+
+/// Download a complete crash bundle from object storage or the user's
+/// runner (admin only).
+#[instrument(skip(state, admin))]
+pub async fn admin_download_crash_bundle(
+    State(state): State<AppState>,
+    RequireAdmin(admin): RequireAdmin,
+    Path((user_id, bundle_id)): Path<(String, String)>,
+) -> ApiResult<impl IntoResponse> {
+    use axum::http::header;
+
+    let service = crash_bundle_service(&state)?;
+    let record = service
+        .repository()
+        .get(&user_id, &bundle_id)
+        .await?
+        .ok_or_else(|| ApiError::not_found(format!("Crash bundle {bundle_id} not found")))?;
+    let archived = service
+        .archived(&user_id, &record.id)
+        .await
+        .unwrap_or_else(|e| {
+            warn!(bundle_id = %record.id, "Reading archived crash bundle failed: {:#}", e);
+            None
+        });
+    let body = match archived {
+        Some(body) => body,
+        None => {
+            let bundle = personal_runner(&state, &user_id)
+                .await?
+                .get_crash_bundle(&record.id)
+                .await
+                .map_err(|e| {
+                    ApiError::not_found(format!("Crash bundle unavailable on runner: {e:#}"))
+                })?;
+            serde_json::to_vec_pretty(&bundle)
+                .map_err(|e| ApiError::internal(format!("Failed to encode crash bundle: {e}")))?
+        }
+    };
+
+    info!(
+        admin_id = %admin.id(),
+        user_id = %user_id,
+        bundle_id = %record.id,
+        session_id = %record.session_id,
+        "Admin downloaded crash bundle"
+    );
+    Ok((
+        [
+            (header::CONTENT_TYPE, "application/json".to_string()),
+            (
+                header::CONTENT_DISPOSITION,
+                format!("attachment; filename=\"{}.json\"", record.id),
+            ),
+        ],
+        body,
+    ))
+}
+
 /// SSE metrics stream (admin only).
 #[instrument(skip(state, _user))]
 pub async fn admin_metrics_stream(
@@ -617,9 +938,28 @@ pub async fn update_user(
     Path(user_id): Path<String>,
     Json(request): Json<UpdateUserRequest>,
 ) -> ApiResult<Json<DbUserInfo>> {
+    // Role, password and activation changes invalidate outstanding tokens.
+    // Saving the form with the role it already had is not a role change.
+    let previous_role = state.users.get_user(&user_id).await?.map(|user| user.role);
+    let revoke_reason = if request.role.is_some() && request.role != previous_role {
+        Some("role changed")
+    } else if request.is_active == Some(false) {
+        Some("account deactivated")
+    } else if request.password.is_some() {
+        Some("password changed")
+    } else {
+        None
+    };
+
     // Uses centralized From<anyhow::Error> conversion
     let user = state.users.update_user(&user_id, request).await?;
 
+    if let Some(reason) = revoke_reason {
+        state
+            .auth
+            .revoke_user_tokens(&user.id, RevocationScope::AllTokens, reason);
+    }
+
     info!(user_id = %user.id, "Updated user");
     Ok(Json(user.into()))
 }
@@ -645,6 +985,9 @@ pub async fn delete_user(
 
     // Delete from oqto DB first
     state.users.delete_user(&user_id).await?;
+    state
+        .auth
+        .revoke_user_tokens(&user_id, RevocationScope::AllTokens, "account deleted");
 
     // In multi-user mode, clean up the Linux user + services
     if let Some(ref linux_user) = linux_username {
@@ -681,6 +1024,9 @@ pub async fn deactivate_user(
 ) -> ApiResult<Json<DbUserInfo>> {
     // Uses centralized From<anyhow::Error> conversion
     let user = state.users.deactivate_user(&user_id).await?;
+    state
+        .auth
+        .revoke_user_tokens(&user.id, RevocationScope::AllTokens, "account deactivated");
 
     info!(user_id = %user.id, "Deactivated user");
     Ok(Json(user.into()))
@@ -712,6 +1058,157 @@ pub async fn get_user_stats(
     Ok(Json(stats))
 }
 
+/// Disk usage of one platform user's Linux account.
+#[derive(Debug, Serialize)]
+pub struct UserDiskUsage {
+    /// Platform user, when the Linux account belongs to one.
+    pub user_id: Option<String>,
+    pub username: Option<String>,
+    pub linux_username: String,
+    pub used_bytes: u64,
+    /// 0 when no limit is set.
+    pub soft_limit_bytes: u64,
+    pub hard_limit_bytes: u64,
+}
+
+/// Disk usage response, largest consumers first.
+#[derive(Debug, Serialize)]
+pub struct DiskUsageResponse {
+    pub users: Vec<UserDiskUsage>,
+}
+
+/// Per-user disk usage and quotas on the filesystem holding /home (admin only).
+#[instrument(skip(state, _user))]
+pub async fn get_disk_usage(
+    State(state): State<AppState>,
+    RequireAdmin(_user): RequireAdmin,
+) -> ApiResult<Json<DiskUsageResponse>> {
+    let linux_users = state
+        .linux_users
+        .clone()
+        .ok_or_else(|| ApiError::bad_request("Linux user isolation is not enabled."))?;
+
+    let usage = tokio::task::spawn_blocking(move || linux_users.disk_usage())
+        .await
+        .map_err(|e| anyhow::anyhow!("Task join error: {e}"))?
+        .map_err(|e| ApiError::internal(format!("Failed to read disk usage: {e:#}")))?;
+
+    let platform_users = state.users.list_users(UserListQuery::default()).await?;
+    let mut users: Vec<UserDiskUsage> = usage
+        .into_iter()
+        .map(|entry| {
+            let owner = platform_users
+                .iter()
+                .find(|u| u.linux_username.as_deref() == Some(entry.username.as_str()));
+            UserDiskUsage {
+                user_id: owner.map(|u| u.id.clone()),
+                username: owner.map(|u| u.username.clone()),
+                linux_username: entry.username,
+                used_bytes: entry.used_bytes,
+                soft_limit_bytes: entry.soft_limit_bytes,
+                hard_limit_bytes: entry.hard_limit_bytes,
+            }
+        })
+        .collect();
+    users.sort_by(|a, b| b.used_bytes.cmp(&a.used_bytes));
+
+    Ok(Json(DiskUsageResponse { users }))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SetUserQuotaRequest {
+    /// Quota in GiB; 0 removes the limit.
+    pub gb: u64,
+}
+
+/// Set a user's disk quota (admin only).
+#[instrument(skip(state, _user))]
+pub async fn set_user_quota(
+    State(state): State<AppState>,
+    RequireAdmin(_user): RequireAdmin,
+    Path(user_id): Path<String>,
+    Json(request): Json<SetUserQuotaRequest>,
+) -> ApiResult<StatusCode> {
+    let linux_users = state
+        .linux_users
+        .clone()
+        .ok_or_else(|| ApiError::bad_request("Linux user isolation is not enabled."))?;
+    let user = state
+        .users
+        .get_user(&user_id)
+        .await?
+        .ok_or_else(|| ApiError::not_found(format!("User {} not found", user_id)))?;
+    let linux_username = user
+        .linux_username
+        .ok_or_else(|| ApiError::bad_request("User has no Linux account."))?;
+
+    let gb = request.gb;
+    let target = linux_username.clone();
+    tokio::task::spawn_blocking(move || linux_users.set_quota(&target, gb))
+        .await
+        .map_err(|e| anyhow::anyhow!("Task join error: {e}"))?
+        .map_err(|e| ApiError::internal(format!("Failed to set quota: {e:#}")))?;
+
+    info!(user_id = %user_id, linux_username = %linux_username, gb, "Set disk quota");
+    Ok(StatusCode::NO_CONTENT)
+}
+
+async fn require_user(state: &AppState, user_id: &str) -> ApiResult<()> {
+    state
+        .users
+        .get_user(user_id)
+        .await?
+        .map(drop)
+        .ok_or_else(|| ApiError::not_found(format!("User {} not found", user_id)))
+}
+
+/// Get a user's container resource limits (admin only).
+#[instrument(skip(state, _user))]
+pub async fn get_user_resource_limits(
+    State(state): State<AppState>,
+    RequireAdmin(_user): RequireAdmin,
+    Path(user_id): Path<String>,
+) -> ApiResult<Json<UserResourceLimits>> {
+    require_user(&state, &user_id).await?;
+    Ok(Json(state.sessions.user_resource_limits(&user_id).await?))
+}
+
+/// Set a user's container resource limits for new sessions (admin only).
+#[instrument(skip(state, _user))]
+pub async fn set_user_resource_limits(
+    State(state): State<AppState>,
+    RequireAdmin(_user): RequireAdmin,
+    Path(user_id): Path<String>,
+    Json(limits): Json<ResourceLimits>,
+) -> ApiResult<Json<UserResourceLimits>> {
+    require_user(&state, &user_id).await?;
+    let limits = state
+        .sessions
+        .set_user_resource_limits(&user_id, Some(&limits))
+        .await?;
+
+    info!(user_id = %user_id, ?limits.overrides, "Set user resource limits");
+    Ok(Json(limits))
+}
+
+/// Reset a user's container resource limits to the configured defaults
+/// (admin only).
+#[instrument(skip(state, _user))]
+pub async fn delete_user_resource_limits(
+    State(state): State<AppState>,
+    RequireAdmin(_user): RequireAdmin,
+    Path(user_id): Path<String>,
+) -> ApiResult<Json<UserResourceLimits>> {
+    require_user(&state, &user_id).await?;
+    let limits = state
+        .sessions
+        .set_user_resource_limits(&user_id, None)
+        .await?;
+
+    info!(user_id = %user_id, "Reset user resource limits");
+    Ok(Json(limits))
+}
+
 /// Provision an EAVS virtual key and Pi models.json for a new user.
 ///
 /// Creates a virtual key for the user (no oauth_user binding -- that would
@@ -0,0 +1,431 @@
+//! Usage analytics handlers.
+
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+use axum::{
+    Json,
+    extract::{Path, Query, State},
+};
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use tracing::{debug, info, instrument, warn};
+
+use crate::auth::{CurrentUser, RequireAdmin};
+use crate::eavs::budget::{BudgetCrossing, BudgetLevel, SessionBudget};
+use crate::eavs::usage::{SessionUsage, UsageSummary, UsageSummaryQuery};
+use crate::eavs::{BudgetService, UsageService};
+use crate::observability::metrics::spend_by_user;
+use crate::runner::router::{ExecutionTarget, resolve_runner_for_session};
+use crate::session_tags::{SessionTag, SessionTagService, TagStat, TagStatsQuery};
+use crate::session_target::SessionTargetScope;
+use crate::tool_usage::{ToolStatsGroupBy, ToolStatsQuery, ToolUsageService, ToolUsageStat};
+use crate::ws::WsEvent;
+
+use crate::api::error::{ApiError, ApiResult};
+use crate::api::state::AppState;
+
+/// Tool statistics response.
+#[derive(Debug, Serialize)]
+pub struct ToolStatsResponse {
+    pub group_by: ToolStatsGroupBy,
+    pub stats: Vec<ToolUsageStat>,
+}
+
+fn tool_usage_service(state: &AppState) -> ApiResult<&ToolUsageService> {
+    state
+        .tool_usage
+        .as_deref()
+        .ok_or_else(|| ApiError::service_unavailable("Tool usage statistics are disabled"))
+}
+
+/// Tool usage statistics for the current user.
+///
+/// GET /api/analytics/tools?group_by=tool|agent|project&sort=calls|failures|failure_rate|avg_duration
+#[instrument(skip(state))]
+pub async fn get_tool_stats(
+    State(state): State<AppState>,
+    user: CurrentUser,
+    Query(query): Query<ToolStatsQuery>,
+) -> ApiResult<Json<ToolStatsResponse>> {
+    if query.group_by == ToolStatsGroupBy::User {
+        return Err(ApiError::bad_request(
+            "group_by=user is only available to admins",
+        ));
+    }
+
+    let stats = tool_usage_service(&state)?
+        .repository()
+        .stats(&query, Some(user.id()))
+        .await
+        .map_err(|e| ApiError::internal(format!("Failed to query tool stats: {e}")))?;
+
+    Ok(Json(ToolStatsResponse {
+        group_by: query.group_by,
+        stats,
+    }))
+}
+
+/// Tool usage statistics and leaderboards across all users (admin only).
+///
+/// GET /api/admin/analytics/tools?group_by=tool|agent|project|user&user_id=...
+#[instrument(skip(state, _user))]
+pub async fn admin_get_tool_stats(
+    State(state): State<AppState>,
+    RequireAdmin(_user): RequireAdmin,
+    Query(query): Query<ToolStatsQuery>,
+) -> ApiResult<Json<ToolStatsResponse>> {
+    let stats = tool_usage_service(&state)?
+        .repository()
+        .stats(&query, None)
+        .await
+        .map_err(|e| ApiError::internal(format!("Failed to query tool stats: {e}")))?;
+
+    Ok(Json(ToolStatsResponse {
+        group_by: query.group_by,
+        stats,
+    }))
+}
+
+fn session_tag_service(state: &AppState) -> ApiResult<&SessionTagService> {
+    state
+        .session_tags
+        .as_deref()
+        .ok_or_else(|| ApiError::service_unavailable("Session tagging is disabled"))
+}
+
+/// Tags of the current user's sessions, keyed by session ID.
+///
+/// GET /api/session-tags
+#[instrument(skip(state))]
+pub async fn list_session_tags(
+    State(state): State<AppState>,
+    user: CurrentUser,
+) -> ApiResult<Json<BTreeMap<String, Vec<SessionTag>>>> {
+    let tags = session_tag_service(&state)?
+        .repository()
+        .for_user(user.id())
+        .await
+        .map_err(|e| ApiError::internal(format!("Failed to list session tags: {e}")))?;
+
+    let mut by_session: BTreeMap<String, Vec<SessionTag>> = BTreeMap::new();
+    for tag in tags {
+        by_session
+            .entry(tag.session_id.clone())
+            .or_default()
+            .push(tag);
+    }
+    Ok(Json(by_session))
+}
+
+/// Sessions per tag for the current user.
+///
+/// GET /api/analytics/tags?kind=topic|language|framework&since=YYYY-MM-DD&until=YYYY-MM-DD
+#[instrument(skip(state))]
+pub async fn get_tag_stats(
+    State(state): State<AppState>,
+    user: CurrentUser,
+    Query(query): Query<TagStatsQuery>,
+) -> ApiResult<Json<Vec<TagStat>>> {
+    let stats = session_tag_service(&state)?
+        .repository()
+        .stats(&query, Some(user.id()))
+        .await
+        .map_err(|e| ApiError::internal(format!("Failed to query tag stats: {e}")))?;
+    Ok(Json(stats))
+}
+
+/// Sessions per tag across all users (admin only).
+///
+/// GET /api/admin/analytics/tags?kind=...&user_id=...
+#[instrument(skip(state, _user))]
+pub async fn admin_get_tag_stats(
+    State(state): State<AppState>,
+    RequireAdmin(_user): RequireAdmin,
+    Query(query): Query<TagStatsQuery>,
+) -> ApiResult<Json<Vec<TagStat>>> {
+    let stats = session_tag_service(&state)?
+        .repository()
+        .stats(&query, None)
+        .await
+        .map_err(|e| ApiError::internal(format!("Failed to query tag stats: {e}")))?;
+    Ok(Json(stats))
+}
+
+fn usage_service(state: &AppState) -> ApiResult<&UsageService> {
+    state
+        .usage
+        .as_deref()
+        .ok_or_else(|| ApiError::service_unavailable("Spend attribution is disabled"))
+}
+
+async fn usage_summary(
+    state: &AppState,
+    query: &UsageSummaryQuery,
+    user_id: Option<&str>,
+    by_user: bool,
+) -> ApiResult<UsageSummary> {
+    let range = query
+        .range(Utc::now().date_naive())
+        .map_err(ApiError::bad_request)?;
+    let mut summary = usage_service(state)?
+        .summary(query, range, user_id, by_user)
+        .await
+        .map_err(|e| ApiError::internal(format!("Failed to query usage: {e}")))?;
+    summary.eavs_billed_usd = eavs_billed_usd(state, user_id).await;
+    Ok(summary)
+}
+
+/// All-time spend EAVS billed to `user_id`'s keys, or to all users' keys.
+async fn eavs_billed_usd(state: &AppState, user_id: Option<&str>) -> Option<f64> {
+    let eavs = state.eavs_client.as_ref()?;
+    let keys = match eavs.list_keys().await {
+        Ok(keys) => keys,
+        Err(e) => {
+            debug!("Failed to list EAVS keys for usage summary: {e}");
+            return None;
+        }
+    };
+    let spend = spend_by_user(&keys);
+    Some(match user_id {
+        Some(user_id) => spend
+            .iter()
+            .find(|(user, _)| user == user_id)
+            .map(|(_, total)| *total)
+            .unwrap_or(0.0),
+        None => spend.iter().map(|(_, total)| total).sum(),
+    })
+}
+
+/// Token counts and spend of the current user, per model, session and time
+/// bucket.
+///
+/// GET /api/usage/summary?since=YYYY-MM-DD&until=YYYY-MM-DD&bucket=day|week|month&limit=50
+#[instrument(skip(state))]
+pub async fn get_usage_summary(
+    State(state): State<AppState>,
+    user: CurrentUser,
+    Query(query): Query<UsageSummaryQuery>,
+) -> ApiResult<Json<UsageSummary>> {
+    if query.user_id.is_some() {
+        return Err(ApiError::bad_request("user_id is only available to admins"));
+    }
+    Ok(Json(
+        usage_summary(&state, &query, Some(user.id()), false).await?,
+    ))
+}
+
+/// Token counts and spend of one of the current user's sessions.
+///
+/// GET /api/usage/sessions/{session_id}
+#[instrument(skip(state))]
+pub async fn get_session_usage(
+    State(state): State<AppState>,
+    user: CurrentUser,
+    Path(session_id): Path<String>,
+) -> ApiResult<Json<SessionUsage>> {
+    usage_service(&state)?
+        .repository()
+        .session(user.id(), &session_id)
+        .await
+        .map_err(|e| ApiError::internal(format!("Failed to query session usage: {e}")))?
+        .map(Json)
+        .ok_or_else(|| ApiError::not_found("No usage recorded for this session"))
+}
+
+/// Token counts and spend across all users, with a per-user breakdown
+/// (admin only).
+///
+/// GET /api/admin/usage/summary?since=...&until=...&bucket=...&user_id=...
+#[instrument(skip(state, _user))]
+pub async fn admin_get_usage_summary(
+    State(state): State<AppState>,
+    RequireAdmin(_user): RequireAdmin,
+    Query(query): Query<UsageSummaryQuery>,
+) -> ApiResult<Json<UsageSummary>> {
+    let user_id = query.user_id.clone();
+    Ok(Json(
+        usage_summary(&state, &query, user_id.as_deref(), true).await?,
+    ))
+}
+
+fn budget_service(state: &AppState) -> ApiResult<&BudgetService> {
+    state
+        .budget
+        .as_deref()
+        .ok_or_else(|| ApiError::service_unavailable("Session budgets are disabled"))
+}
+
+/// Budget state of one of the current user's sessions.
+///
+/// GET /api/usage/sessions/{session_id}/budget
+#[instrument(skip(state))]
+pub async fn get_session_budget(
+    State(state): State<AppState>,
+    user: CurrentUser,
+    Path(session_id): Path<String>,
+) -> ApiResult<Json<SessionBudget>> {
+    budget_service(&state)?
+        .status(&session_id)
+        .await
+        .map_err(|e| ApiError::internal(format!("Failed to query session budget: {e}")))?
+        .filter(|budget| budget.user_id == user.id())
+        .map(Json)
+        .ok_or_else(|| ApiError::not_found("No usage recorded for this session"))
+}
+
+/// Query parameters for listing flagged session budgets.
+#[derive(Debug, Deserialize)]
+pub struct BudgetListQuery {
+    /// Only sessions at this level (`warning` or `exceeded`).
+    pub level: Option<BudgetLevel>,
+}
+
+/// Sessions that reached their budget's warning share or the budget
+/// itself, most recent first (admin only).
+///
+/// GET /api/admin/budget/sessions?level=warning|exceeded
+#[instrument(skip(state, _user))]
+pub async fn admin_list_session_budgets(
+    State(state): State<AppState>,
+    RequireAdmin(_user): RequireAdmin,
+    Query(query): Query<BudgetListQuery>,
+) -> ApiResult<Json<Vec<SessionBudget>>> {
+    let budgets = budget_service(&state)?
+        .flagged(query.level)
+        .await
+        .map_err(|e| ApiError::internal(format!("Failed to list session budgets: {e}")))?;
+    Ok(Json(budgets))
+}
+
+/// Request to set a session's budget.
+#[derive(Debug, Deserialize)]
+pub struct SetSessionBudgetRequest {
+    /// Budget in USD; `null` reverts to `[budget] session_limit_usd`.
+    pub limit_usd: Option<f64>,
+}
+
+/// Set a session's budget (admin only). Raising it above the spend lifts a
+/// suspension; lowering it may warn or suspend the session right away.
+///
+/// PUT /api/admin/budget/sessions/{session_id}
+#[instrument(skip(state, user))]
+pub async fn admin_set_session_budget(
+    State(state): State<AppState>,
+    RequireAdmin(user): RequireAdmin,
+    Path(session_id): Path<String>,
+    Json(request): Json<SetSessionBudgetRequest>,
+) -> ApiResult<Json<SessionBudget>> {
+    if request
+        .limit_usd
+        .is_some_and(|limit| !limit.is_finite() || limit < 0.0)
+    {
+        return Err(ApiError::bad_request(
+            "limit_usd must be a non-negative number",
+        ));
+    }
+    let (budget, crossing) = budget_service(&state)?
+        .set_limit(&session_id, request.limit_usd)
+        .await
+        .map_err(|e| ApiError::internal(format!("Failed to set session budget: {e}")))?
+        .ok_or_else(|| ApiError::not_found("No usage recorded for this session"))?;
+    info!(
+        admin_id = %user.id(),
+        session_id = %session_id,
+        limit_usd = ?request.limit_usd,
+        "Set session budget"
+    );
+    if let Some(crossing) = crossing {
+        enforce_budget(&state, &crossing).await;
+    }
+    Ok(Json(budget))
+}
+
+/// Check session spend against budgets every `check_interval_seconds`.
+pub async fn run_budget_checks(state: AppState) {
+    let Some(budget) = state.budget.clone() else {
+        return;
+    };
+    let mut interval = tokio::time::interval(Duration::from_secs(
+        budget.config().check_interval_seconds.max(1),
+    ));
+    loop {
+        interval.tick().await;
+        match budget.check().await {
+            Ok(crossings) => {
+                for crossing in &crossings {
+                    enforce_budget(&state, crossing).await;
+                }
+            }
+            Err(e) => warn!("Failed to check session budgets: {e:#}"),
+        }
+    }
+}
+
+/// Announce a budget crossing to the session owner and, when it suspends
+/// the session, abort the agent.
+async fn enforce_budget(state: &AppState, crossing: &BudgetCrossing) {
+    warn!(
+        user_id = %crossing.user_id,
+        session_id = %crossing.session_id,
+        level = ?crossing.level,
+        spent_usd = crossing.spent_usd,
+        limit_usd = crossing.limit_usd,
+        "Session budget threshold reached"
+    );
+    if crossing.suspend
+        && let Err(e) = abort_session(state, &crossing.user_id, &crossing.session_id).await
+    {
+        warn!(session_id = %crossing.session_id, "Failed to abort session over budget: {e:#}");
+    }
+    if crossing.level == BudgetLevel::Exceeded
+        && let Some(webhooks) = &state.webhooks
+    {
+        webhooks
+            .emit(
+                crate::webhooks::WebhookEvent::BudgetExceeded,
+                serde_json::json!({
+                    "session_id": crossing.session_id,
+                    "user_id": crossing.user_id,
+                    "spent_usd": crossing.spent_usd,
+                    "limit_usd": crossing.limit_usd,
+                    "suspended": crossing.suspend,
+                }),
+            )
+            .await;
+    }
+
+    let event = oqto_protocol::events::Event {
+        session_id: crossing.session_id.clone(),
+        runner_id: "local".to_string(),
+        ts: Utc::now().timestamp_millis(),
+        seq: None,
+        payload: crossing.payload(),
+    };
+    state
+        .ws_hub
+        .send_to_user(
+            &crossing.user_id,
+            WsEvent::AgentEvent {
+                session_id: crossing.session_id.clone(),
+                event: serde_json::to_value(&event).unwrap_or_default(),
+            },
+        )
+        .await;
+}
+
+async fn abort_session(state: &AppState, user_id: &str, session_id: &str) -> anyhow::Result<()> {
+    let target = match state.session_targets.get(session_id).await? {
+        Some(record) => match (record.scope, record.workspace_id) {
+            (SessionTargetScope::SharedWorkspace, Some(workspace_id)) => {
+                ExecutionTarget::SharedWorkspace { workspace_id }
+            }
+            _ => ExecutionTarget::Personal,
+        },
+        None => ExecutionTarget::Personal,
+    };
+    match resolve_runner_for_session(state, user_id, session_id, &target, false).await? {
+        Some(runner) => runner.agent_abort(session_id).await,
+        None => Ok(()),
+    }
+}
@@ -0,0 +1,280 @@
+//! Message annotation handlers: reactions, flags and notes on agent responses.
+
+use axum::{
+    Json,
+    extract::{Path, Query, State},
+    http::StatusCode,
+};
+use oqto_runner::protocol::WorkspaceChatMessagesSource;
+use tracing::{info, instrument, warn};
+
+use crate::auth::{CurrentUser, RequireAdmin};
+use crate::message_annotations::{
+    AnnotationKind, AnnotationRepository, CreateAnnotationRequest, FeedbackStatsQuery,
+    MessageAnnotation, ModelFeedbackStat, NewAnnotation,
+};
+use crate::shared_workspace::SharePermission;
+use crate::ws::types::WsEvent;
+
+use super::bookmarks::{require_chat_read, session_access};
+use super::chat::{SessionArtifactQuery, session_runner};
+use crate::api::error::{ApiError, ApiResult};
+use crate::api::state::AppState;
+
+/// Longest accepted flag name.
+const MAX_FLAG_LEN: usize = 32;
+/// Longest accepted note.
+const MAX_NOTE_LEN: usize = 2000;
+/// Most annotations in one session.
+const MAX_ANNOTATIONS_PER_SESSION: i64 = 2000;
+
+fn annotations(state: &AppState) -> ApiResult<&AnnotationRepository> {
+    state
+        .annotations
+        .as_deref()
+        .ok_or_else(|| ApiError::not_found("Annotations are not available"))
+}
+
+/// Check and normalize the value of an annotation.
+fn checked_value(kind: AnnotationKind, value: &str) -> ApiResult<String> {
+    let value = value.trim();
+    match kind {
+        AnnotationKind::Reaction => match value {
+            "up" | "down" => Ok(value.to_string()),
+            _ => Err(ApiError::bad_request("reaction must be 'up' or 'down'")),
+        },
+        AnnotationKind::Flag => {
+            let flag = value.to_lowercase();
+            if flag.is_empty()
+                || flag.len() > MAX_FLAG_LEN
+                || !flag
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+            {
+                return Err(ApiError::bad_request(format!(
+                    "flag must be 1-{MAX_FLAG_LEN} letters, digits, '-' or '_'"
+                )));
+            }
+            Ok(flag)
+        }
+        AnnotationKind::Note => {
+            if value.is_empty() || value.chars().count() > MAX_NOTE_LEN {
+                return Err(ApiError::bad_request(format!(
+                    "note must be 1 to {MAX_NOTE_LEN} characters"
+                )));
+            }
+            Ok(value.to_string())
+        }
+    }
+}
+
+/// Tell everyone who can see an annotation that it changed: its author,
+/// and in shared workspaces the members who can read the session.
+async fn publish(state: &AppState, annotation: &MessageAnnotation, removed: bool) {
+    let mut recipients = vec![annotation.user_id.clone()];
+    if let (Some(workspace_id), Some(service)) =
+        (&annotation.workspace_id, &state.shared_workspaces)
+    {
+        match service
+            .list_members(workspace_id, &annotation.user_id)
+            .await
+        {
+            Ok(members) => recipients.extend(
+                members
+                    .into_iter()
+                    .filter(|m| m.permissions.allows(SharePermission::ChatRead))
+                    .map(|m| m.user_id)
+                    .filter(|id| *id != annotation.user_id),
+            ),
+            Err(e) => warn!(
+                workspace_id = %workspace_id,
+                "Failed to list workspace members for annotation event: {e:#}"
+            ),
+        }
+    }
+    let annotation_json = serde_json::to_value(annotation).unwrap_or_default();
+    for user_id in recipients {
+        state
+            .ws_hub
+            .send_to_user(
+                &user_id,
+                WsEvent::MessageAnnotation {
+                    session_id: annotation.session_id.clone(),
+                    message_id: annotation.message_id.clone(),
+                    annotation: annotation_json.clone(),
+                    removed,
+                },
+            )
+            .await;
+    }
+}
+
+/// React to, flag or add a note to an agent response. The annotation
+/// records the provider and model that produced the message.
+#[instrument(skip(state, user, request), fields(kind = request.kind.as_str()))]
+pub async fn create_annotation(
+    State(state): State<AppState>,
+    user: CurrentUser,
+    Path((session_id, message_id)): Path<(String, String)>,
+    Query(query): Query<SessionArtifactQuery>,
+    Json(request): Json<CreateAnnotationRequest>,
+) -> ApiResult<(StatusCode, Json<MessageAnnotation>)> {
+    let repo = annotations(&state)?;
+    let value = checked_value(request.kind, &request.value)?;
+
+    let workspace_id = session_access(
+        &state,
+        user.id(),
+        &session_id,
+        query.shared_workspace_id.as_deref(),
+    )
+    .await?;
+    if repo.count_for_session(&session_id).await? >= MAX_ANNOTATIONS_PER_SESSION {
+        return Err(ApiError::too_many_requests(
+            "Too many annotations in this session",
+        ));
+    }
+
+    let runner = session_runner(&state, user.id(), &session_id, workspace_id.as_deref()).await?;
+    let message = runner
+        .get_workspace_chat_session_messages(
+            &session_id,
+            false,
+            None,
+            WorkspaceChatMessagesSource::Authoritative,
+        )
+        .await
+        .map_err(|e| ApiError::internal(format!("runner get messages failed: {e:#}")))?
+        .messages
+        .into_iter()
+        .find(|m| m.id == message_id)
+        .ok_or_else(|| ApiError::not_found(format!("Message {message_id} not found")))?;
+    if message.role != "assistant" {
+        return Err(ApiError::bad_request(
+            "Only agent responses can be annotated",
+        ));
+    }
+
+    let annotation = repo
+        .create(&NewAnnotation {
+            session_id: &session_id,
+            message_id: &message_id,
+            user_id: user.id(),
+            workspace_id: workspace_id.as_deref(),
+            kind: request.kind,
+            value: &value,
+            provider: message.provider_id.as_deref(),
+            model: message.model_id.as_deref(),
+        })
+        .await?;
+    info!(
+        session_id = %session_id,
+        message_id = %message_id,
+        annotation_id = %annotation.id,
+        "Annotated message"
+    );
+    publish(&state, &annotation, false).await;
+    Ok((StatusCode::CREATED, Json(annotation)))
+}
+
+/// Annotations of a session, oldest first: shared ones, and the caller's.
+#[instrument(skip(state, user))]
+pub async fn list_annotations(
+    State(state): State<AppState>,
+    user: CurrentUser,
+    Path(session_id): Path<String>,
+    Query(query): Query<SessionArtifactQuery>,
+) -> ApiResult<Json<Vec<MessageAnnotation>>> {
+    let repo = annotations(&state)?;
+    session_access(
+        &state,
+        user.id(),
+        &session_id,
+        query.shared_workspace_id.as_deref(),
+    )
+    .await?;
+    Ok(Json(repo.list_for_session(&session_id, user.id()).await?))
+}
+
+/// Remove an annotation. Only its author can.
+#[instrument(skip(state, user))]
+pub async fn delete_annotation(
+    State(state): State<AppState>,
+    user: CurrentUser,
+    Path(annotation_id): Path<String>,
+) -> ApiResult<StatusCode> {
+    let repo = annotations(&state)?;
+    let not_found = || ApiError::not_found(format!("Annotation {annotation_id} not found"));
+    let annotation = repo.get(&annotation_id).await?.ok_or_else(not_found)?;
+    if annotation.user_id != user.id() {
+        // Hide other users' private annotations.
+        return match &annotation.workspace_id {
+            Some(workspace_id) => {
+                require_chat_read(&state, user.id(), workspace_id)
+                    .await
+                    .map_err(|_| not_found())?;
+                Err(ApiError::forbidden(
+                    "Only its author can remove an annotation",
+                ))
+            }
+            None => Err(not_found()),
+        };
+    }
+    repo.delete(&annotation.id).await?;
+    publish(&state, &annotation, true).await;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Feedback on agent responses per model, from the current user's
+/// annotations.
+///
+/// GET /api/analytics/feedback?since=YYYY-MM-DD&until=YYYY-MM-DD&provider=...
+#[instrument(skip(state))]
+pub async fn get_feedback_stats(
+    State(state): State<AppState>,
+    user: CurrentUser,
+    Query(query): Query<FeedbackStatsQuery>,
+) -> ApiResult<Json<Vec<ModelFeedbackStat>>> {
+    let stats = annotations(&state)?
+        .stats(&query, Some(user.id()))
+        .await
+        .map_err(|e| ApiError::internal(format!("Failed to query feedback stats: {e}")))?;
+    Ok(Json(stats))
+}
+
+/// Feedback on agent responses per model across all users (admin only).
+///
+/// GET /api/admin/analytics/feedback?user_id=...
+#[instrument(skip(state, _user))]
+pub async fn admin_get_feedback_stats(
+    State(state): State<AppState>,
+    RequireAdmin(_user): RequireAdmin,
+    Query(query): Query<FeedbackStatsQuery>,
+) -> ApiResult<Json<Vec<ModelFeedbackStat>>> {
+    let stats = annotations(&state)?
+        .stats(&query, None)
+        .await
+        .map_err(|e| ApiError::internal(format!("Failed to query feedback stats: {e}")))?;
+    Ok(Json(stats))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn annotation_values_are_checked() {
+        assert_eq!(
+            checked_value(AnnotationKind::Reaction, " up ").unwrap(),
+            "up"
+        );
+        assert!(checked_value(AnnotationKind::Reaction, "meh").is_err());
+        assert_eq!(
+            checked_value(AnnotationKind::Flag, "Hallucination").unwrap(),
+            "hallucination"
+        );
+        assert!(checked_value(AnnotationKind::Flag, "made up").is_err());
+        assert!(checked_value(AnnotationKind::Note, "  ").is_err());
+        assert!(checked_value(AnnotationKind::Note, &"x".repeat(MAX_NOTE_LEN + 1)).is_err());
+    }
+}
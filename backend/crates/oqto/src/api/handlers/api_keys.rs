@@ -1,4 +1,7 @@
 //! API key management handlers.
+//!
+//! API keys double as personal access tokens: `/keys` is the original
+//! route, `/tokens` the one documented for scripts and CI.
 
 use axum::{
     Json,
@@ -7,13 +10,14 @@ use axum::{
 use serde::Serialize;
 use tracing::instrument;
 
+use super::impersonation::refuse_impersonated;
 use crate::api::error::{ApiError, ApiResult};
 use crate::api::state::AppState;
 use crate::api_keys::{
-    ApiKeyCreateRequest, ApiKeyCreateResponse, ApiKeyListItem, generate_api_key, hash_api_key,
-    normalize_expires_at,
+    ApiKeyCreateRequest, ApiKeyCreateResponse, ApiKeyListItem, SCOPE_ADMIN, generate_api_key,
+    hash_api_key, normalize_expires_at, normalize_scopes,
 };
-use crate::auth::CurrentUser;
+use crate::auth::{CurrentUser, RevocationScope};
 
 const OMNI_KEY_NAME: &str = "omni-vanilla";
 
@@ -43,6 +47,8 @@ pub async fn create_api_key(
     user: CurrentUser,
     Json(request): Json<ApiKeyCreateRequest>,
 ) -> ApiResult<Json<ApiKeyCreateResponse>> {
+    // A key would outlive the impersonation and carry no trace of the admin.
+    refuse_impersonated(&user, "API keys cannot be created")?;
     let name = request.name.trim();
     if name.is_empty() {
         return Err(ApiError::bad_request("API key name is required"));
@@ -56,7 +62,19 @@ pub async fn create_api_key(
         None => None,
     };
 
-    let scopes = request.scopes.unwrap_or_default();
+    let scopes = normalize_scopes(request.scopes.as_deref())
+        .map_err(|e| ApiError::bad_request(format!("Invalid scopes: {e}")))?;
+    if scopes.iter().any(|s| s == SCOPE_ADMIN) && !user.is_admin() {
+        return Err(ApiError::forbidden(
+            "Only admins can create keys with the 'admin' scope",
+        ));
+    }
+    // A key must not mint a key that can do more than itself.
+    if let Some(missing) = scopes.iter().find(|s| !user.claims.has_scope(s)) {
+        return Err(ApiError::forbidden(format!(
+            "This token lacks the '{missing}' scope it would grant"
+        )));
+    }
 
     if name == OMNI_KEY_NAME {
         // Revoke existing keys with the same name so omni links stay stable.
@@ -79,6 +97,22 @@ pub async fn create_api_key(
     Ok(Json(ApiKeyCreateResponse { api_key, key }))
 }
 
+/// Get one of the current user's API keys by id.
+#[instrument(skip(state))]
+pub async fn get_api_key(
+    State(state): State<AppState>,
+    user: CurrentUser,
+    Path(key_id): Path<String>,
+) -> ApiResult<Json<ApiKeyListItem>> {
+    state
+        .api_keys
+        .get_for_user(user.id(), &key_id)
+        .await
+        .map_err(|e| ApiError::internal(format!("Failed to get api key: {e}")))?
+        .map(Json)
+        .ok_or_else(|| ApiError::not_found("API key not found"))
+}
+
 /// Revoke an API key by id.
 #[instrument(skip(state))]
 pub async fn revoke_api_key(
@@ -96,6 +130,11 @@ pub async fn revoke_api_key(
         return Err(ApiError::not_found("API key not found"));
     }
 
+    // Drop live connections that authenticated with an API key.
+    state
+        .auth
+        .revoke_user_tokens(user.id(), RevocationScope::ApiKeys, "api key revoked");
+
     Ok(())
 }
 
@@ -116,5 +155,9 @@ pub async fn delete_api_key(
         return Err(ApiError::not_found("API key not found"));
     }
 
+    state
+        .auth
+        .revoke_user_tokens(user.id(), RevocationScope::ApiKeys, "api key deleted");
+
     Ok(())
 }
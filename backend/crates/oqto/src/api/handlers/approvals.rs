@@ -0,0 +1,142 @@
+//! Tool approval handlers.
+//!
+//! With `[runner.tool_approvals]` enabled, tool calls matching an approval
+//! rule pause the agent and emit `tool.approval_required`. The call runs
+//! once the user approves it here; a denial (with an optional reason for
+//! the agent) blocks it.
+
+use axum::{
+    Json,
+    extract::{Path, Query, State},
+};
+use serde::Deserialize;
+use tracing::{info, instrument};
+
+use oqto_runner::protocol::{ErrorCode, ErrorResponse, ToolApproval};
+
+use crate::auth::CurrentUser;
+
+use super::chat::{SessionArtifactQuery, session_runner};
+use crate::api::error::{ApiError, ApiResult};
+use crate::api::state::AppState;
+
+/// Longest accepted denial reason.
+const MAX_REASON_LEN: usize = 500;
+
+/// Body of `POST /sessions/{session_id}/approvals/{approval_id}`.
+#[derive(Debug, Deserialize)]
+pub struct ResolveApprovalRequest {
+    pub approved: bool,
+    /// Passed on to the agent with a denial.
+    #[serde(default)]
+    pub reason: Option<String>,
+}
+
+fn approval_error(err: anyhow::Error) -> ApiError {
+    match err.downcast_ref::<ErrorResponse>() {
+        Some(e) => match e.code {
+            ErrorCode::ToolApprovalNotFound => {
+                ApiError::not_found("Approval is not pending (already resolved or timed out)")
+            }
+            ErrorCode::PiSessionNotFound => ApiError::not_found("Session is not running"),
+            _ => ApiError::internal(format!("Tool approval failed: {}", e.message)),
+        },
+        None => ApiError::internal(format!("Tool approval failed: {err:#}")),
+    }
+}
+
+/// Tool calls of a session waiting for approval, oldest first.
+#[instrument(skip(state, user))]
+pub async fn list_session_approvals(
+    State(state): State<AppState>,
+    user: CurrentUser,
+    Path(session_id): Path<String>,
+    Query(query): Query<SessionArtifactQuery>,
+) -> ApiResult<Json<Vec<ToolApproval>>> {
+    let runner = session_runner(
+        &state,
+        user.id(),
+        &session_id,
+        query.shared_workspace_id.as_deref(),
+    )
+    .await?;
+    let approvals = runner
+        .list_tool_approvals(&session_id)
+        .await
+        .map_err(approval_error)?;
+    Ok(Json(approvals))
+}
+
+/// Approve or deny a paused tool call. The agent resumes either way.
+#[instrument(skip(state, user, request))]
+pub async fn resolve_session_approval(
+    State(state): State<AppState>,
+    user: CurrentUser,
+    Path((session_id, approval_id)): Path<(String, String)>,
+    Query(query): Query<SessionArtifactQuery>,
+    Json(request): Json<ResolveApprovalRequest>,
+) -> ApiResult<Json<ToolApproval>> {
+    let reason = request
+        .reason
+        .as_deref()
+        .map(str::trim)
+        .filter(|r| !r.is_empty());
+    if reason.is_some_and(|r| r.len() > MAX_REASON_LEN) {
+        return Err(ApiError::bad_request(format!(
+            "reason must be at most {MAX_REASON_LEN} characters"
+        )));
+    }
+
+    let runner = session_runner(
+        &state,
+        user.id(),
+        &session_id,
+        query.shared_workspace_id.as_deref(),
+    )
+    .await?;
+    let approval = runner
+        .resolve_tool_approval(
+            &session_id,
+            &approval_id,
+            request.approved,
+            reason.map(str::to_string),
+        )
+        .await
+        .map_err(approval_error)?;
+    info!(
+        user_id = %user.id(),
+        session_id = %session_id,
+        tool = %approval.name,
+        rule = %approval.rule,
+        approved = request.approved,
+        "tool call approval resolved"
+    );
+    Ok(Json(approval))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn runner_errors_map_to_statuses() {
+        let runner_error = |code| {
+            anyhow::Error::from(ErrorResponse {
+                code,
+                message: "x".to_string(),
+            })
+        };
+        assert!(matches!(
+            approval_error(runner_error(ErrorCode::ToolApprovalNotFound)),
+            ApiError::NotFound(_)
+        ));
+        assert!(matches!(
+            approval_error(runner_error(ErrorCode::PiSessionNotFound)),
+            ApiError::NotFound(_)
+        ));
+        assert!(matches!(
+            approval_error(runner_error(ErrorCode::Internal)),
+            ApiError::Internal(_)
+        ));
+    }
+}
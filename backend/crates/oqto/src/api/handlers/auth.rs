@@ -1,17 +1,30 @@
 //! Authentication handlers.
 
+use std::net::SocketAddr;
+
 use axum::{
     Json,
-    extract::State,
-    http::{StatusCode, header::SET_COOKIE},
-    response::{AppendHeaders, IntoResponse},
+    extract::{ConnectInfo, Path, Query, State},
+    http::{Extensions, HeaderMap, StatusCode, header::SET_COOKIE},
+    response::{AppendHeaders, IntoResponse, Redirect, Response},
 };
 use serde::{Deserialize, Serialize};
 use tracing::{error, info, instrument, warn};
+use webauthn_rs::prelude::{
+    CreationChallengeResponse, PublicKeyCredential, RegisterPublicKeyCredential,
+    RequestChallengeResponse,
+};
 
-use crate::auth::{AuthError, CurrentUser};
-use crate::user::{CreateUserRequest, UpdateUserRequest, UserInfo as DbUserInfo};
+use crate::audit::AuthAudit;
+use crate::auth::{
+    AuthError, CurrentUser, DeviceAuthorization, DevicePoll, GroupRoles, LOGIN_COOKIE, LOGIN_TTL,
+    OidcIdentity, OidcProvider, PasskeyInfo, PasskeyService, login_cookie,
+};
+use crate::net;
+use crate::registration::RegistrationStatus;
+use crate::user::{CreateUserRequest, UpdateUserRequest, User, UserInfo as DbUserInfo, UserRole};
 
+use super::impersonation::refuse_impersonated;
 use crate::api::error::{ApiError, ApiResult};
 use crate::api::state::AppState;
 
@@ -111,7 +124,9 @@ pub struct RegisterRequest {
     pub username: String,
     pub email: String,
     pub password: String,
-    pub invite_code: String,
+    /// Required unless open registration is enabled.
+    #[serde(default)]
+    pub invite_code: Option<String>,
     pub display_name: Option<String>,
 }
 
@@ -120,30 +135,80 @@ pub struct RegisterRequest {
 pub struct RegisterResponse {
     pub token: String,
     pub user: UserInfo,
+    /// Whether the account is quarantined until the email address is
+    /// verified.
+    pub email_verification_pending: bool,
+}
+
+/// Response to an open registration that waits for admin approval.
+#[derive(Debug, Serialize)]
+pub struct PendingRegistrationResponse {
+    pub status: RegistrationStatus,
+    pub user: UserInfo,
+    /// Whether a verification email was sent.
+    pub email_verification_pending: bool,
 }
 
-/// Register a new user with invite code.
+/// Register a new user with an invite code, or without one when open
+/// registration is enabled.
 ///
 /// This operation is designed to be safe against race conditions:
 /// 1. Atomically consume the invite code (prevents double-use)
 /// 2. Create the user
 /// 3. If user creation fails, restore the invite code use
-#[instrument(skip(state, request), fields(username = %request.username))]
+///
+/// Open registrations are checked against the registration limits instead
+/// and recorded right after the user is created. When they need approval,
+/// the account is disabled and 202 is returned without a token.
+#[instrument(skip(state, extensions, headers, request), fields(username = %request.username))]
 pub async fn register(
     State(state): State<AppState>,
+    extensions: Extensions,
+    headers: HeaderMap,
     Json(request): Json<RegisterRequest>,
-) -> ApiResult<impl IntoResponse> {
-    // Atomically consume the invite code first.
-    // This prevents TOQTOU race conditions where two requests could both
-    // validate and then both try to use the same single-use code.
-    let _invite_code_id = state
-        .invites
-        .try_consume_atomic(&request.invite_code, "pending") // Use "pending" as placeholder
-        .await
-        .map_err(|e| {
-            tracing::error!("Invite code consumption failed: {e:#}");
-            ApiError::bad_request(format!("{e:#}"))
-        })?;
+) -> ApiResult<Response> {
+    let invite_code = request
+        .invite_code
+        .clone()
+        .filter(|code| !code.trim().is_empty());
+
+    let open_registration = match &invite_code {
+        Some(code) => {
+            // Atomically consume the invite code first.
+            // This prevents TOQTOU race conditions where two requests could both
+            // validate and then both try to use the same single-use code.
+            state
+                .invites
+                .try_consume_atomic(code, "pending") // Use "pending" as placeholder
+                .await
+                .map_err(|e| {
+                    tracing::error!("Invite code consumption failed: {e:#}");
+                    ApiError::bad_request(format!("{e:#}"))
+                })?;
+            None
+        }
+        None => {
+            let service = state
+                .registration
+                .as_ref()
+                .filter(|service| service.is_open())
+                .ok_or_else(|| ApiError::bad_request("An invite code is required"))?;
+            let client_ip = net::client_ip(&extensions).map(|ip| ip.to_string());
+            if let Some(refusal) = service.admit(&request.email, client_ip.as_deref()).await? {
+                info!(
+                    client_ip = client_ip.as_deref().unwrap_or("unknown"),
+                    "Refused open registration: {}",
+                    refusal.message()
+                );
+                return Err(if refusal.is_rate_limit() {
+                    ApiError::too_many_requests(refusal.message())
+                } else {
+                    ApiError::forbidden(refusal.message())
+                });
+            }
+            Some((service.clone(), client_ip))
+        }
+    };
 
     // SECURITY: In multi-user mode, generate a user_id that won't collide with existing
     // Linux users BEFORE creating the DB user. This avoids the need for rollback.
@@ -152,7 +217,9 @@ pub async fn register(
             Ok(id) => Some(id),
             Err(e) => {
                 // Restore the invite code
-                if let Err(restore_err) = state.invites.restore_use(&request.invite_code).await {
+                if let Some(code) = &invite_code
+                    && let Err(restore_err) = state.invites.restore_use(code).await
+                {
                     warn!(
                         "Failed to restore invite code after ID generation failure: {:?}",
                         restore_err
@@ -200,7 +267,9 @@ pub async fn register(
         Ok(user) => user,
         Err(e) => {
             // User creation failed - restore the invite code use
-            if let Err(restore_err) = state.invites.restore_use(&request.invite_code).await {
+            if let Some(code) = &invite_code
+                && let Err(restore_err) = state.invites.restore_use(code).await
+            {
                 warn!(
                     "Failed to restore invite code use after user creation failure: {:?}",
                     restore_err
@@ -210,6 +279,40 @@ pub async fn register(
         }
     };
 
+    // Record an open registration before anything else is provisioned, and
+    // disable the account if it waits for approval.
+    let registration = match &open_registration {
+        Some((service, client_ip)) => {
+            let recorded = match service
+                .record(&user.id, &user.email, client_ip.as_deref())
+                .await
+            {
+                Ok(registration) if registration.status == RegistrationStatus::PendingApproval => {
+                    state
+                        .users
+                        .deactivate_user(&user.id)
+                        .await
+                        .map(|_| registration)
+                }
+                other => other,
+            };
+            match recorded {
+                Ok(registration) => Some(registration),
+                Err(e) => {
+                    if let Err(delete_err) = state.users.delete_user(&user.id).await {
+                        error!(
+                            user_id = %user.id,
+                            error = ?delete_err,
+                            "Failed to delete user after recording the registration failed"
+                        );
+                    }
+                    return Err(e.into());
+                }
+            }
+        }
+        None => None,
+    };
+
     // SECURITY: Create Linux user if multi-user isolation is enabled.
     // Since we pre-generated a unique ID, this should succeed unless there's a system error.
     // Track the linux username for later use (eavs provisioning etc.)
@@ -287,7 +390,9 @@ pub async fn register(
                 }
 
                 // Restore the invite code
-                if let Err(restore_err) = state.invites.restore_use(&request.invite_code).await {
+                if let Some(code) = &invite_code
+                    && let Err(restore_err) = state.invites.restore_use(code).await
+                {
                     warn!(
                         "Failed to restore invite code after rollback: {:?}",
                         restore_err
@@ -302,14 +407,14 @@ pub async fn register(
         }
     }
 
-    // Update the invite code to record the actual user ID
-    if let Err(e) = sqlx::query("UPDATE invite_codes SET used_by = ? WHERE code = ?")
-        .bind(&user.id)
-        .bind(&request.invite_code)
-        .execute(state.invites.pool())
-        .await
+    // Record who redeemed the invite code, now that the user exists
+    if let Some(code) = &invite_code
+        && let Err(e) = state
+            .invites
+            .record_redemption(code, &user.id, &user.username)
+            .await
     {
-        warn!("Failed to update invite code used_by: {:?}", e);
+        warn!("Failed to record invite code redemption: {:?}", e);
     }
 
     crate::api::provisioning::bootstrap_new_user_environment(
@@ -319,6 +424,27 @@ pub async fn register(
     )
     .await;
 
+    let email_verification_pending = registration.as_ref().is_some_and(|r| r.quarantined);
+    if let Some(registration) = registration
+        && registration.status == RegistrationStatus::PendingApproval
+    {
+        info!(user_id = %user.id, username = %user.username, "User registered, awaiting approval");
+        return Ok((
+            StatusCode::ACCEPTED,
+            Json(PendingRegistrationResponse {
+                status: registration.status,
+                user: UserInfo {
+                    id: user.id,
+                    name: user.display_name,
+                    email: user.email,
+                    role: user.role.to_string(),
+                },
+                email_verification_pending,
+            }),
+        )
+            .into_response());
+    }
+
     // Generate JWT token for the new user
     let token = state.auth.generate_token(
         &user.id,
@@ -353,16 +479,103 @@ pub async fn register(
                 email: user.email,
                 role: user.role.to_string(),
             },
+            email_verification_pending,
         }),
-    ))
+    )
+        .into_response())
+}
+
+/// Record an authentication event in the audit log, with the request's
+/// origin.
+async fn audit_auth(
+    state: &AppState,
+    extensions: &Extensions,
+    headers: &HeaderMap,
+    event: &str,
+    mut details: AuthAudit<'_>,
+) {
+    let Some(logger) = state.audit_logger.as_ref() else {
+        return;
+    };
+    details.client_ip = extensions
+        .get::<ConnectInfo<SocketAddr>>()
+        .map(|ConnectInfo(addr)| addr.ip().to_string());
+    details.forwarded_for = headers.get("x-forwarded-for").and_then(|v| v.to_str().ok());
+    logger.log_auth(event, details).await;
+}
+
+/// Prepare the Linux account of a user who signs in, when per-user
+/// isolation is enabled.
+async fn ensure_user_runtime(state: &AppState, db_user: &User) -> ApiResult<()> {
+    if let Some(ref linux_users) = state.linux_users {
+        let ensure_result = if let (Some(linux_username), Some(linux_uid)) =
+            (db_user.linux_username.as_ref(), db_user.linux_uid)
+        {
+            linux_users.ensure_user_with_verification(
+                &db_user.id,
+                Some(linux_username),
+                Some(linux_uid as u32),
+            )
+        } else if state.strict_identity_enabled() {
+            return Err(ApiError::conflict(format!(
+                "strict identity mode is enabled, but user '{}' is missing linux_username/linux_uid; run 'oqtoctl user doctor-identity --apply'",
+                db_user.username
+            )));
+        } else {
+            warn!(
+                user_id = %db_user.id,
+                "using legacy identity fallback (ensure_user by user_id); run identity migration and enable strict mode"
+            );
+            linux_users.ensure_user(&db_user.id)
+        };
+
+        match ensure_result {
+            Ok((uid, actual_linux_username)) => {
+                if (db_user.linux_username.as_deref() != Some(actual_linux_username.as_str())
+                    || db_user.linux_uid != Some(uid as i64))
+                    && let Err(e) = state
+                        .users
+                        .update_user(
+                            &db_user.id,
+                            crate::user::UpdateUserRequest {
+                                linux_username: Some(actual_linux_username.clone()),
+                                linux_uid: Some(uid as i64),
+                                ..Default::default()
+                            },
+                        )
+                        .await
+                {
+                    warn!(
+                        user_id = %db_user.id,
+                        error = %e,
+                        "Failed to store linux_username/uid in database"
+                    );
+                }
+            }
+            Err(e) => {
+                return Err(ApiError::internal(format!(
+                    "Failed to initialize user runtime: {e:#}"
+                )));
+            }
+        }
+    }
+    Ok(())
 }
 
 /// Login endpoint (works with database users).
-#[instrument(skip(state, request), fields(username = %request.username))]
+#[instrument(skip(state, extensions, headers, request), fields(username = %request.username))]
 pub async fn login(
     State(state): State<AppState>,
+    extensions: Extensions,
+    headers: HeaderMap,
     Json(request): Json<LoginRequest>,
 ) -> ApiResult<impl IntoResponse> {
+    let login_failed = || AuthAudit {
+        username: Some(request.username.as_str()),
+        reason: Some("invalid_credentials"),
+        ..Default::default()
+    };
+
     // Try to verify against database users first
     let user = state
         .users
@@ -371,59 +584,7 @@ pub async fn login(
 
     let (token, user_info) = match user {
         Some(db_user) => {
-            if let Some(ref linux_users) = state.linux_users {
-                let ensure_result = if let (Some(linux_username), Some(linux_uid)) =
-                    (db_user.linux_username.as_ref(), db_user.linux_uid)
-                {
-                    linux_users.ensure_user_with_verification(
-                        &db_user.id,
-                        Some(linux_username),
-                        Some(linux_uid as u32),
-                    )
-                } else if state.strict_identity_enabled() {
-                    return Err(ApiError::conflict(format!(
-                        "strict identity mode is enabled, but user '{}' is missing linux_username/linux_uid; run 'oqtoctl user doctor-identity --apply'",
-                        db_user.username
-                    )));
-                } else {
-                    warn!(
-                        user_id = %db_user.id,
-                        "using legacy identity fallback (ensure_user by user_id); run identity migration and enable strict mode"
-                    );
-                    linux_users.ensure_user(&db_user.id)
-                };
-
-                match ensure_result {
-                    Ok((uid, actual_linux_username)) => {
-                        if (db_user.linux_username.as_deref()
-                            != Some(actual_linux_username.as_str())
-                            || db_user.linux_uid != Some(uid as i64))
-                            && let Err(e) = state
-                                .users
-                                .update_user(
-                                    &db_user.id,
-                                    crate::user::UpdateUserRequest {
-                                        linux_username: Some(actual_linux_username.clone()),
-                                        linux_uid: Some(uid as i64),
-                                        ..Default::default()
-                                    },
-                                )
-                                .await
-                        {
-                            warn!(
-                                user_id = %db_user.id,
-                                error = %e,
-                                "Failed to store linux_username/uid in database"
-                            );
-                        }
-                    }
-                    Err(e) => {
-                        return Err(ApiError::internal(format!(
-                            "Failed to initialize user runtime: {e:#}"
-                        )));
-                    }
-                }
-            }
+            ensure_user_runtime(&state, &db_user).await?;
 
             // Database user found and verified
             let token = state.auth.generate_token(
@@ -443,10 +604,20 @@ pub async fn login(
         None => {
             // Fall back to dev mode credentials if enabled
             if state.auth.is_dev_mode() {
-                let dev_user = state
+                let Some(dev_user) = state
                     .auth
                     .validate_dev_credentials(&request.username, &request.password)
-                    .ok_or_else(|| ApiError::unauthorized("Invalid username or password"))?;
+                else {
+                    audit_auth(
+                        &state,
+                        &extensions,
+                        &headers,
+                        "auth_login_failed",
+                        login_failed(),
+                    )
+                    .await;
+                    return Err(ApiError::unauthorized("Invalid username or password"));
+                };
 
                 if let Some(ref linux_users) = state.linux_users {
                     let (uid, linux_username) =
@@ -466,6 +637,14 @@ pub async fn login(
                 };
                 (token, user_info)
             } else {
+                audit_auth(
+                    &state,
+                    &extensions,
+                    &headers,
+                    "auth_login_failed",
+                    login_failed(),
+                )
+                .await;
                 return Err(ApiError::unauthorized("Invalid username or password"));
             }
         }
@@ -485,6 +664,18 @@ pub async fn login(
     );
 
     info!(user_id = %user_info.id, "User logged in successfully");
+    audit_auth(
+        &state,
+        &extensions,
+        &headers,
+        "auth_login",
+        AuthAudit {
+            user_id: Some(&user_info.id),
+            username: Some(&request.username),
+            ..Default::default()
+        },
+    )
+    .await;
 
     Ok((
         AppendHeaders([(SET_COOKIE, cookie)]),
@@ -506,6 +697,664 @@ pub async fn logout() -> impl IntoResponse {
     )
 }
 
+/// Query of `GET /api/auth/oidc/login`.
+#[derive(Debug, Deserialize)]
+pub struct OidcLoginQuery {
+    /// Path in the app to open after signing in.
+    pub return_to: Option<String>,
+}
+
+/// Start signing in through the OpenID Connect provider: redirects the
+/// browser to it, with a cookie binding the sign-in to this browser.
+#[instrument(skip(state, query))]
+pub async fn oidc_login(
+    State(state): State<AppState>,
+    Query(query): Query<OidcLoginQuery>,
+) -> ApiResult<Response> {
+    let provider = state
+        .oidc
+        .as_ref()
+        .ok_or_else(|| ApiError::not_found("Single sign-on is not enabled"))?;
+    let login = provider
+        .begin_login(query.return_to.as_deref())
+        .await
+        .map_err(|e| {
+            error!("Failed to start OIDC sign-in: {e:#}");
+            ApiError::bad_gateway("The identity provider is not reachable")
+        })?;
+    let cookie = oidc_login_cookie(&state, &login.cookie, LOGIN_TTL.as_secs());
+    Ok((
+        AppendHeaders([(SET_COOKIE, cookie)]),
+        Redirect::to(&login.url),
+    )
+        .into_response())
+}
+
+/// `Set-Cookie` value for the sign-in in progress. Lax, so it is sent on
+/// the provider's top-level redirect back to the callback.
+fn oidc_login_cookie(state: &AppState, value: &str, max_age: u64) -> String {
+    let secure_flag = if state.auth.is_dev_mode() {
+        ""
+    } else {
+        " Secure;"
+    };
+    format!(
+        "{}={value}; Path=/api/auth/oidc; HttpOnly; SameSite=Lax;{secure_flag} Max-Age={max_age}",
+        LOGIN_COOKIE
+    )
+}
+
+/// Query the provider redirects back with.
+#[derive(Debug, Deserialize)]
+pub struct OidcCallbackQuery {
+    pub code: Option<String>,
+    pub state: Option<String>,
+    pub error: Option<String>,
+    pub error_description: Option<String>,
+}
+
+/// Why a single sign-on failed: an audit reason and a message for the
+/// login page.
+struct SsoFailure {
+    reason: &'static str,
+    message: String,
+    email: Option<String>,
+}
+
+impl SsoFailure {
+    fn new(reason: &'static str, message: impl Into<String>) -> Self {
+        Self {
+            reason,
+            message: message.into(),
+            email: None,
+        }
+    }
+}
+
+/// Finish signing in through the OpenID Connect provider. Sets the auth
+/// cookie and sends the browser back into the app, or to the login page
+/// with `sso_error` when sign-in failed.
+#[instrument(skip_all)]
+pub async fn oidc_callback(
+    State(state): State<AppState>,
+    extensions: Extensions,
+    headers: HeaderMap,
+    Query(query): Query<OidcCallbackQuery>,
+) -> ApiResult<Response> {
+    let provider = state
+        .oidc
+        .clone()
+        .ok_or_else(|| ApiError::not_found("Single sign-on is not enabled"))?;
+
+    // The sign-in is over either way.
+    let clear_login = oidc_login_cookie(&state, "", 0);
+    let login_cookie = login_cookie(&headers);
+    let (token, user, return_to) = match oidc_sign_in(&state, &provider, query, login_cookie).await
+    {
+        Ok(signed_in) => signed_in,
+        Err(failure) => {
+            audit_auth(
+                &state,
+                &extensions,
+                &headers,
+                "auth_login_failed",
+                AuthAudit {
+                    username: failure.email.as_deref(),
+                    reason: Some(failure.reason),
+                    ..Default::default()
+                },
+            )
+            .await;
+            let location = format!("/login?sso_error={}", urlencoding::encode(&failure.message));
+            return Ok((
+                AppendHeaders([(SET_COOKIE, clear_login)]),
+                Redirect::to(&location),
+            )
+                .into_response());
+        }
+    };
+
+    let secure_flag = if state.auth.is_dev_mode() {
+        ""
+    } else {
+        " Secure;"
+    };
+    let cookie = format!(
+        "auth_token={}; Path=/; HttpOnly; SameSite=Lax;{} Max-Age={}",
+        token,
+        secure_flag,
+        60 * 60 * 24 // 24 hours
+    );
+
+    info!(user_id = %user.id, "User logged in through single sign-on");
+    audit_auth(
+        &state,
+        &extensions,
+        &headers,
+        "auth_login",
+        AuthAudit {
+            user_id: Some(&user.id),
+            username: Some(&user.email),
+            ..Default::default()
+        },
+    )
+    .await;
+
+    Ok((
+        AppendHeaders([(SET_COOKIE, cookie), (SET_COOKIE, clear_login)]),
+        Redirect::to(&return_to),
+    )
+        .into_response())
+}
+
+/// Redeem the provider's answer and find, create and update the user.
+/// `login_cookie` is the browser's sign-in in progress, which the
+/// provider's `state` must belong to.
+async fn oidc_sign_in(
+    state: &AppState,
+    provider: &OidcProvider,
+    query: OidcCallbackQuery,
+    login_cookie: Option<&str>,
+) -> Result<(String, User, String), SsoFailure> {
+    if let Some(error) = query.error {
+        warn!(error = %error, "Identity provider refused sign-in");
+        return Err(SsoFailure::new(
+            "provider_error",
+            query.error_description.unwrap_or(error),
+        ));
+    }
+    let (Some(code), Some(sso_state)) = (query.code, query.state) else {
+        return Err(SsoFailure::new("invalid_request", "Missing code or state"));
+    };
+    let login = provider
+        .complete_login(&sso_state, &code, login_cookie)
+        .await
+        .map_err(|e| {
+            warn!("OIDC sign-in failed: {e:#}");
+            SsoFailure::new("sso_failed", "Sign-in failed; please try again")
+        })?;
+    let (token, user) = oidc_user(state, provider, login.identity).await?;
+    Ok((token, user, login.return_to))
+}
+
+/// Find, create and update the user the provider vouched for and issue a
+/// session token.
+async fn oidc_user(
+    state: &AppState,
+    provider: &OidcProvider,
+    identity: OidcIdentity,
+) -> Result<(String, User), SsoFailure> {
+    let failure = |reason, message: String| SsoFailure {
+        reason,
+        message,
+        email: Some(identity.email.clone()),
+    };
+
+    let config = provider.config();
+    if !config.allows(&identity.groups) {
+        return Err(failure(
+            "group_not_allowed",
+            "Your account is not allowed to use this server".to_string(),
+        ));
+    }
+    let user = state
+        .users
+        .get_or_create_from_oidc(
+            &identity.subject,
+            &identity.email,
+            identity.email_verified,
+            &identity.name,
+            config.auto_provision,
+        )
+        .await
+        .map_err(|e| failure("sso_failed", e.to_string()))?
+        .ok_or_else(|| {
+            failure(
+                "unknown_user",
+                format!(
+                    "No account exists for {}; ask an administrator for access",
+                    identity.email
+                ),
+            )
+        })?;
+    if !user.is_active {
+        return Err(failure(
+            "account_disabled",
+            "Your account is disabled or waiting for approval".to_string(),
+        ));
+    }
+    let user = match config.group_roles(&identity.groups) {
+        Some(roles) => apply_group_roles(state, user, roles).await.map_err(|e| {
+            error!("Failed to apply OIDC group roles: {e:#}");
+            failure("sso_failed", "Sign-in failed; please try again".to_string())
+        })?,
+        None => user,
+    };
+
+    ensure_user_runtime(state, &user).await.map_err(|e| {
+        error!(user_id = %user.id, "Failed to prepare user runtime: {e}");
+        failure(
+            "sso_failed",
+            "Your workspace could not be prepared; please contact an administrator".to_string(),
+        )
+    })?;
+    let token = state
+        .auth
+        .generate_token(
+            &user.id,
+            &user.email,
+            &user.display_name,
+            &user.role.to_string(),
+        )
+        .map_err(|e| {
+            error!("Failed to issue token: {e}");
+            failure("sso_failed", "Sign-in failed; please try again".to_string())
+        })?;
+    Ok((token, user))
+}
+
+/// Start a device sign-in through the OpenID Connect provider, for clients
+/// without a browser. The user enters the returned code at the provider.
+#[instrument(skip(state))]
+pub async fn oidc_device_start(
+    State(state): State<AppState>,
+) -> ApiResult<Json<DeviceAuthorization>> {
+    let provider = state
+        .oidc
+        .as_ref()
+        .ok_or_else(|| ApiError::not_found("Single sign-on is not enabled"))?;
+    let authorization = provider.begin_device_login().await.map_err(|e| {
+        error!("Failed to start OIDC device sign-in: {e:#}");
+        ApiError::bad_gateway("The identity provider does not offer device sign-in")
+    })?;
+    Ok(Json(authorization))
+}
+
+/// Body of `POST /api/auth/oidc/device/token`.
+#[derive(Debug, Deserialize)]
+pub struct DeviceTokenRequest {
+    pub device_code: String,
+}
+
+/// Where a device sign-in stands; `complete` carries the session token.
+#[derive(Debug, Serialize)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum DeviceTokenResponse {
+    Pending,
+    SlowDown,
+    Complete(LoginResponse),
+}
+
+/// Poll a device sign-in started with `POST /api/auth/oidc/device`.
+#[instrument(skip_all)]
+pub async fn oidc_device_token(
+    State(state): State<AppState>,
+    extensions: Extensions,
+    headers: HeaderMap,
+    Json(request): Json<DeviceTokenRequest>,
+) -> ApiResult<Json<DeviceTokenResponse>> {
+    let provider = state
+        .oidc
+        .clone()
+        .ok_or_else(|| ApiError::not_found("Single sign-on is not enabled"))?;
+    let identity = match provider.poll_device_login(&request.device_code).await {
+        Ok(DevicePoll::Pending) => return Ok(Json(DeviceTokenResponse::Pending)),
+        Ok(DevicePoll::SlowDown) => return Ok(Json(DeviceTokenResponse::SlowDown)),
+        Ok(DevicePoll::Complete(identity)) => identity,
+        Err(e) => {
+            warn!("OIDC device sign-in failed: {e:#}");
+            return Err(ApiError::bad_request(
+                "Sign-in was denied or expired; please start again",
+            ));
+        }
+    };
+
+    let (token, user) = match oidc_user(&state, &provider, identity).await {
+        Ok(signed_in) => signed_in,
+        Err(failure) => {
+            audit_auth(
+                &state,
+                &extensions,
+                &headers,
+                "auth_login_failed",
+                AuthAudit {
+                    username: failure.email.as_deref(),
+                    reason: Some(failure.reason),
+                    ..Default::default()
+                },
+            )
+            .await;
+            return Err(ApiError::forbidden(failure.message));
+        }
+    };
+
+    info!(user_id = %user.id, "User logged in through device sign-on");
+    audit_auth(
+        &state,
+        &extensions,
+        &headers,
+        "auth_login",
+        AuthAudit {
+            user_id: Some(&user.id),
+            username: Some(&user.email),
+            ..Default::default()
+        },
+    )
+    .await;
+
+    Ok(Json(DeviceTokenResponse::Complete(LoginResponse {
+        token,
+        user: UserInfo {
+            id: user.id,
+            name: user.display_name,
+            email: user.email,
+            role: user.role.to_string(),
+        },
+    })))
+}
+
+/// Give the user the role and RBAC roles their IdP groups map to. Service
+/// accounts keep their role.
+async fn apply_group_roles(
+    state: &AppState,
+    mut user: User,
+    mapped: GroupRoles,
+) -> anyhow::Result<User> {
+    let role = if mapped.admin {
+        UserRole::Admin
+    } else {
+        UserRole::User
+    };
+    if user.role != UserRole::Service && user.role != role {
+        info!(user_id = %user.id, from = %user.role, to = %role, "Role changed by IdP groups");
+        user = state
+            .users
+            .update_user(
+                &user.id,
+                UpdateUserRequest {
+                    role: Some(role),
+                    ..Default::default()
+                },
+            )
+            .await?;
+    }
+
+    let Some(rbac) = state.rbac.as_ref() else {
+        return Ok(user);
+    };
+    let known = rbac.repo().list_roles().await?;
+    let (roles, unknown): (Vec<String>, Vec<String>) = mapped
+        .roles
+        .into_iter()
+        .partition(|name| known.iter().any(|r| &r.name == name));
+    if !unknown.is_empty() {
+        warn!(roles = ?unknown, "[auth.oidc] group_roles names roles that do not exist");
+    }
+    if rbac.repo().user_roles(&user.id).await? != roles {
+        rbac.repo().set_user_roles(&user.id, &roles, "oidc").await?;
+        rbac.invalidate(Some(&user.id));
+        info!(user_id = %user.id, roles = ?roles, "RBAC roles set by IdP groups");
+    }
+    Ok(user)
+}
+
+fn passkey_service(state: &AppState) -> ApiResult<&PasskeyService> {
+    state
+        .passkeys
+        .as_deref()
+        .ok_or_else(|| ApiError::not_found("Passkey sign-in is not enabled"))
+}
+
+/// The current user's passkeys.
+#[instrument(skip(state, user))]
+pub async fn list_passkeys(
+    State(state): State<AppState>,
+    user: CurrentUser,
+) -> ApiResult<Json<Vec<PasskeyInfo>>> {
+    let passkeys = passkey_service(&state)?;
+    Ok(Json(passkeys.list(user.id()).await?))
+}
+
+/// Response of `POST /api/auth/passkeys/register/start`.
+#[derive(Debug, Serialize)]
+pub struct PasskeyRegistrationStart {
+    pub registration_id: String,
+    /// Options for `navigator.credentials.create()`.
+    pub options: CreationChallengeResponse,
+}
+
+/// Start adding a passkey to the current user.
+#[instrument(skip(state, user))]
+pub async fn start_passkey_registration(
+    State(state): State<AppState>,
+    user: CurrentUser,
+) -> ApiResult<Json<PasskeyRegistrationStart>> {
+    let passkeys = passkey_service(&state)?;
+    // A passkey would outlive the impersonation and sign the admin in as the
+    // user.
+    refuse_impersonated(&user, "Passkeys cannot be added")?;
+    let db_user = state
+        .users
+        .get_user(user.id())
+        .await?
+        .ok_or_else(|| ApiError::not_found("User not found"))?;
+    let (registration_id, options) = passkeys
+        .begin_registration(&db_user.id, &db_user.username, &db_user.display_name)
+        .await
+        .map_err(|e| ApiError::bad_request(format!("{e:#}")))?;
+    Ok(Json(PasskeyRegistrationStart {
+        registration_id,
+        options,
+    }))
+}
+
+/// Body of `POST /api/auth/passkeys/register/finish`.
+#[derive(Debug, Deserialize)]
+pub struct PasskeyRegistrationFinish {
+    pub registration_id: String,
+    /// Name to tell the passkey apart by, e.g. "YubiKey" or "Laptop".
+    pub name: String,
+    /// What `navigator.credentials.create()` returned.
+    pub credential: RegisterPublicKeyCredential,
+}
+
+/// Check and store the passkey the browser created.
+#[instrument(skip_all, fields(user_id = %user.id()))]
+pub async fn finish_passkey_registration(
+    State(state): State<AppState>,
+    user: CurrentUser,
+    extensions: Extensions,
+    headers: HeaderMap,
+    Json(request): Json<PasskeyRegistrationFinish>,
+) -> ApiResult<Json<PasskeyInfo>> {
+    let passkeys = passkey_service(&state)?;
+    refuse_impersonated(&user, "Passkeys cannot be added")?;
+    let passkey = passkeys
+        .finish_registration(
+            user.id(),
+            &request.registration_id,
+            &request.name,
+            &request.credential,
+        )
+        .await
+        .map_err(|e| ApiError::bad_request(format!("{e:#}")))?;
+
+    info!(passkey_id = %passkey.id, "User added a passkey");
+    audit_auth(
+        &state,
+        &extensions,
+        &headers,
+        "auth_passkey_added",
+        AuthAudit {
+            user_id: Some(user.id()),
+            ..Default::default()
+        },
+    )
+    .await;
+    Ok(Json(passkey))
+}
+
+/// Remove one of the current user's passkeys.
+#[instrument(skip(state, user, extensions, headers))]
+pub async fn delete_passkey(
+    State(state): State<AppState>,
+    user: CurrentUser,
+    extensions: Extensions,
+    headers: HeaderMap,
+    Path(passkey_id): Path<String>,
+) -> ApiResult<StatusCode> {
+    let passkeys = passkey_service(&state)?;
+    refuse_impersonated(&user, "Passkeys cannot be removed")?;
+    if !passkeys.delete(user.id(), &passkey_id).await? {
+        return Err(ApiError::not_found("Passkey not found"));
+    }
+    info!(user_id = %user.id(), "User removed a passkey");
+    audit_auth(
+        &state,
+        &extensions,
+        &headers,
+        "auth_passkey_removed",
+        AuthAudit {
+            user_id: Some(user.id()),
+            ..Default::default()
+        },
+    )
+    .await;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Body of `POST /api/auth/passkeys/login/start`.
+#[derive(Debug, Deserialize)]
+pub struct PasskeyLoginStartRequest {
+    pub username: String,
+}
+
+/// Response of `POST /api/auth/passkeys/login/start`.
+#[derive(Debug, Serialize)]
+pub struct PasskeyLoginStart {
+    pub login_id: String,
+    /// Options for `navigator.credentials.get()`.
+    pub options: RequestChallengeResponse,
+}
+
+/// Start signing in with a passkey.
+#[instrument(skip(state, request), fields(username = %request.username))]
+pub async fn start_passkey_login(
+    State(state): State<AppState>,
+    Json(request): Json<PasskeyLoginStartRequest>,
+) -> ApiResult<Json<PasskeyLoginStart>> {
+    let passkeys = passkey_service(&state)?;
+    // Unknown, disabled and passkey-less accounts look the same.
+    let no_passkeys = || ApiError::bad_request("No passkey is registered for this account");
+    let user = state
+        .users
+        .get_user_by_username(&request.username)
+        .await?
+        .filter(|user| user.is_active)
+        .ok_or_else(no_passkeys)?;
+    let (login_id, options) = passkeys
+        .begin_login(&user.id)
+        .await
+        .map_err(|e| ApiError::internal(format!("Failed to start passkey sign-in: {e:#}")))?
+        .ok_or_else(no_passkeys)?;
+    Ok(Json(PasskeyLoginStart { login_id, options }))
+}
+
+/// Body of `POST /api/auth/passkeys/login/finish`.
+#[derive(Debug, Deserialize)]
+pub struct PasskeyLoginFinishRequest {
+    pub login_id: String,
+    /// What `navigator.credentials.get()` returned.
+    pub credential: PublicKeyCredential,
+}
+
+/// Finish signing in with a passkey: sets the auth cookie and returns the
+/// session token like `POST /api/auth/login`.
+#[instrument(skip_all)]
+pub async fn finish_passkey_login(
+    State(state): State<AppState>,
+    extensions: Extensions,
+    headers: HeaderMap,
+    Json(request): Json<PasskeyLoginFinishRequest>,
+) -> ApiResult<impl IntoResponse> {
+    let passkeys = passkey_service(&state)?;
+    let user_id = match passkeys
+        .finish_login(&request.login_id, &request.credential)
+        .await
+    {
+        Ok(user_id) => user_id,
+        Err(e) => {
+            warn!("Passkey sign-in failed: {e:#}");
+            audit_auth(
+                &state,
+                &extensions,
+                &headers,
+                "auth_login_failed",
+                AuthAudit {
+                    reason: Some("invalid_passkey"),
+                    ..Default::default()
+                },
+            )
+            .await;
+            return Err(ApiError::unauthorized("Passkey sign-in failed"));
+        }
+    };
+    // The account may have been disabled since the sign-in started.
+    let db_user = state
+        .users
+        .get_user(&user_id)
+        .await?
+        .filter(|user| user.is_active)
+        .ok_or_else(|| ApiError::unauthorized("Passkey sign-in failed"))?;
+    state.users.record_login(&db_user.id).await?;
+    ensure_user_runtime(&state, &db_user).await?;
+
+    let token = state.auth.generate_token(
+        &db_user.id,
+        &db_user.email,
+        &db_user.display_name,
+        &db_user.role.to_string(),
+    )?;
+    let secure_flag = if state.auth.is_dev_mode() {
+        ""
+    } else {
+        " Secure;"
+    };
+    let cookie = format!(
+        "auth_token={}; Path=/; HttpOnly; SameSite=Lax;{} Max-Age={}",
+        token,
+        secure_flag,
+        60 * 60 * 24 // 24 hours
+    );
+
+    info!(user_id = %db_user.id, "User logged in with a passkey");
+    audit_auth(
+        &state,
+        &extensions,
+        &headers,
+        "auth_login",
+        AuthAudit {
+            user_id: Some(&db_user.id),
+            username: Some(&db_user.username),
+            ..Default::default()
+        },
+    )
+    .await;
+
+    Ok((
+        AppendHeaders([(SET_COOKIE, cookie)]),
+        Json(LoginResponse {
+            token,
+            user: UserInfo {
+                id: db_user.id,
+                name: db_user.display_name,
+                email: db_user.email,
+                role: db_user.role.to_string(),
+            },
+        }),
+    ))
+}
+
 /// Get current user profile.
 #[instrument(skip(state, user))]
 pub async fn get_me(
@@ -572,10 +1421,12 @@ pub struct ChangePasswordRequest {
 }
 
 /// Change current user's password (self-service).
-#[instrument(skip(state, user, request))]
+#[instrument(skip(state, user, extensions, headers, request))]
 pub async fn change_password(
     State(state): State<AppState>,
     user: CurrentUser,
+    extensions: Extensions,
+    headers: HeaderMap,
     Json(request): Json<ChangePasswordRequest>,
 ) -> ApiResult<StatusCode> {
     // Look up the user to get their username for credential verification.
@@ -592,6 +1443,19 @@ pub async fn change_password(
         .await?;
 
     if verified.is_none() {
+        audit_auth(
+            &state,
+            &extensions,
+            &headers,
+            "auth_password_change_failed",
+            AuthAudit {
+                user_id: Some(user.id()),
+                username: Some(&db_user.username),
+                reason: Some("invalid_credentials"),
+                ..Default::default()
+            },
+        )
+        .await;
         return Err(ApiError::unauthorized("Current password is incorrect"));
     }
 
@@ -603,6 +1467,18 @@ pub async fn change_password(
 
     state.users.update_user(user.id(), update).await?;
     info!(user_id = %user.id(), "User changed their password");
+    audit_auth(
+        &state,
+        &extensions,
+        &headers,
+        "auth_password_changed",
+        AuthAudit {
+            user_id: Some(user.id()),
+            username: Some(&db_user.username),
+            ..Default::default()
+        },
+    )
+    .await;
 
     Ok(StatusCode::NO_CONTENT)
 }
@@ -0,0 +1,227 @@
+//! Session bookmark handlers: named points in a session's timeline.
+
+use axum::{
+    Json,
+    extract::{Path, Query, State},
+    http::StatusCode,
+};
+use tracing::instrument;
+
+use crate::auth::CurrentUser;
+use crate::bookmarks::{
+    BookmarkRepository, CreateBookmarkRequest, NewBookmark, SessionBookmark, UpdateBookmarkRequest,
+};
+use crate::session_target::SessionTargetScope;
+use crate::shared_workspace::SharePermission;
+
+use super::chat::{SessionArtifactQuery, session_runner};
+use crate::api::error::{ApiError, ApiResult};
+use crate::api::state::AppState;
+
+/// Longest accepted label.
+const MAX_LABEL_LEN: usize = 200;
+/// Most bookmarks in one session.
+const MAX_BOOKMARKS_PER_SESSION: i64 = 500;
+
+fn bookmarks(state: &AppState) -> ApiResult<&BookmarkRepository> {
+    state
+        .bookmarks
+        .as_deref()
+        .ok_or_else(|| ApiError::not_found("Bookmarks are not available"))
+}
+
+fn label(label: &str) -> ApiResult<&str> {
+    let label = label.trim();
+    if label.is_empty() || label.len() > MAX_LABEL_LEN {
+        return Err(ApiError::bad_request(format!(
+            "label must be 1 to {MAX_LABEL_LEN} characters"
+        )));
+    }
+    Ok(label)
+}
+
+/// Error unless the caller may read sessions of the shared workspace.
+pub(super) async fn require_chat_read(
+    state: &AppState,
+    user_id: &str,
+    workspace_id: &str,
+) -> ApiResult<()> {
+    let service = state
+        .shared_workspaces
+        .as_ref()
+        .ok_or_else(|| ApiError::not_found("Shared workspaces are not enabled"))?;
+    if !service
+        .permissions_for_workspace(workspace_id, user_id)
+        .await?
+        .allows(SharePermission::ChatRead)
+    {
+        return Err(ApiError::forbidden(
+            "Missing 'chat_read' permission in this shared workspace",
+        ));
+    }
+    Ok(())
+}
+
+/// Check that the caller can read the session. Returns its shared workspace,
+/// or None for a personal session of the caller.
+pub(super) async fn session_access(
+    state: &AppState,
+    user_id: &str,
+    session_id: &str,
+    shared_workspace_id: Option<&str>,
+) -> ApiResult<Option<String>> {
+    let workspace_id = match shared_workspace_id {
+        Some(id) => Some(id.to_string()),
+        None => state
+            .session_targets
+            .get(session_id)
+            .await?
+            .filter(|record| record.scope == SessionTargetScope::SharedWorkspace)
+            .and_then(|record| record.workspace_id),
+    };
+    if let Some(workspace_id) = &workspace_id {
+        require_chat_read(state, user_id, workspace_id).await?;
+    }
+
+    let runner = session_runner(state, user_id, session_id, workspace_id.as_deref()).await?;
+    runner
+        .get_workspace_chat_session(session_id)
+        .await
+        .map_err(|e| ApiError::internal(format!("runner get session failed: {e:#}")))?
+        .session
+        .ok_or_else(|| ApiError::not_found(format!("Session {session_id} not found")))?;
+    Ok(workspace_id)
+}
+
+/// Load a bookmark the caller can see. Hidden ones are reported as missing.
+async fn visible_bookmark(
+    state: &AppState,
+    bookmark_id: &str,
+    user_id: &str,
+) -> ApiResult<SessionBookmark> {
+    let not_found = || ApiError::not_found(format!("Bookmark {bookmark_id} not found"));
+    let bookmark = bookmarks(state)?
+        .get(bookmark_id)
+        .await?
+        .ok_or_else(not_found)?;
+    match &bookmark.workspace_id {
+        Some(workspace_id) => require_chat_read(state, user_id, workspace_id)
+            .await
+            .map_err(|_| not_found())?,
+        None if bookmark.user_id != user_id => return Err(not_found()),
+        None => {}
+    }
+    Ok(bookmark)
+}
+
+/// Bookmark a message in a session.
+#[instrument(skip(state, user, request))]
+pub async fn create_bookmark(
+    State(state): State<AppState>,
+    user: CurrentUser,
+    Path(session_id): Path<String>,
+    Query(query): Query<SessionArtifactQuery>,
+    Json(request): Json<CreateBookmarkRequest>,
+) -> ApiResult<(StatusCode, Json<SessionBookmark>)> {
+    let repo = bookmarks(&state)?;
+    let label = label(&request.label)?;
+    let message_id = request.message_id.trim();
+    if message_id.is_empty() {
+        return Err(ApiError::bad_request("message_id is required"));
+    }
+
+    let workspace_id = session_access(
+        &state,
+        user.id(),
+        &session_id,
+        query.shared_workspace_id.as_deref(),
+    )
+    .await?;
+    if repo.count_for_session(&session_id).await? >= MAX_BOOKMARKS_PER_SESSION {
+        return Err(ApiError::too_many_requests(
+            "Too many bookmarks in this session",
+        ));
+    }
+
+    let bookmark = repo
+        .create(&NewBookmark {
+            session_id: &session_id,
+            user_id: user.id(),
+            workspace_id: workspace_id.as_deref(),
+            label,
+            message_id,
+            message_index: request.message_index,
+        })
+        .await?;
+    Ok((StatusCode::CREATED, Json(bookmark)))
+}
+
+/// Bookmarks of a session in timeline order: shared ones, and the caller's.
+#[instrument(skip(state, user))]
+pub async fn list_bookmarks(
+    State(state): State<AppState>,
+    user: CurrentUser,
+    Path(session_id): Path<String>,
+    Query(query): Query<SessionArtifactQuery>,
+) -> ApiResult<Json<Vec<SessionBookmark>>> {
+    let repo = bookmarks(&state)?;
+    session_access(
+        &state,
+        user.id(),
+        &session_id,
+        query.shared_workspace_id.as_deref(),
+    )
+    .await?;
+    Ok(Json(repo.list_for_session(&session_id, user.id()).await?))
+}
+
+/// Resolve a bookmark (the target of a deep link).
+#[instrument(skip(state, user))]
+pub async fn get_bookmark(
+    State(state): State<AppState>,
+    user: CurrentUser,
+    Path(bookmark_id): Path<String>,
+) -> ApiResult<Json<SessionBookmark>> {
+    Ok(Json(
+        visible_bookmark(&state, &bookmark_id, user.id()).await?,
+    ))
+}
+
+/// Rename a bookmark. Only its creator can.
+#[instrument(skip(state, user, request))]
+pub async fn update_bookmark(
+    State(state): State<AppState>,
+    user: CurrentUser,
+    Path(bookmark_id): Path<String>,
+    Json(request): Json<UpdateBookmarkRequest>,
+) -> ApiResult<Json<SessionBookmark>> {
+    let bookmark = visible_bookmark(&state, &bookmark_id, user.id()).await?;
+    if bookmark.user_id != user.id() {
+        return Err(ApiError::forbidden(
+            "Only its creator can rename a bookmark",
+        ));
+    }
+    let label = label(&request.label)?;
+    bookmarks(&state)?.rename(&bookmark.id, label).await?;
+    Ok(Json(SessionBookmark {
+        label: label.to_string(),
+        ..bookmark
+    }))
+}
+
+/// Delete a bookmark. Only its creator can.
+#[instrument(skip(state, user))]
+pub async fn delete_bookmark(
+    State(state): State<AppState>,
+    user: CurrentUser,
+    Path(bookmark_id): Path<String>,
+) -> ApiResult<StatusCode> {
+    let bookmark = visible_bookmark(&state, &bookmark_id, user.id()).await?;
+    if bookmark.user_id != user.id() {
+        return Err(ApiError::forbidden(
+            "Only its creator can delete a bookmark",
+        ));
+    }
+    bookmarks(&state)?.delete(&bookmark.id).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
@@ -33,6 +33,30 @@ pub struct ChatHistoryQuery {
     pub limit: Option<usize>,
     /// If set, list sessions from this shared workspace's runner instead of personal.
     pub shared_workspace_id: Option<String>,
+    /// Comma-separated session tags; only sessions carrying all of them are listed.
+    pub tags: Option<String>,
+}
+
+impl ChatHistoryQuery {
+    fn tag_filter(&self) -> Vec<String> {
+        self.tags
+            .as_deref()
+            .unwrap_or_default()
+            .split(',')
+            .map(|tag| tag.trim().to_lowercase())
+            .filter(|tag| !tag.is_empty())
+            .collect()
+    }
+
+    /// Limit to request from the runner. Tag filtering happens afterwards, so
+    /// it needs the unlimited list.
+    fn runner_limit(&self) -> Option<usize> {
+        if self.tag_filter().is_empty() {
+            self.limit
+        } else {
+            None
+        }
+    }
 }
 
 /// Check if multi-user mode is enabled (linux_users configured).
@@ -57,6 +81,28 @@ fn retain_sessions_in_allowed_workspace(sessions: &mut Vec<ChatSession>, allowed
     sessions.retain(|session| is_within_path(&session.workspace_path, allowed_root));
 }
 
+/// Keep only sessions carrying all of `tags` (no-op without tags).
+async fn retain_sessions_with_tags(
+    state: &AppState,
+    sessions: &mut Vec<ChatSession>,
+    tags: &[String],
+) -> ApiResult<()> {
+    if tags.is_empty() {
+        return Ok(());
+    }
+    let service = state
+        .session_tags
+        .as_ref()
+        .ok_or_else(|| ApiError::service_unavailable("Session tagging is disabled"))?;
+    let tagged = service
+        .repository()
+        .sessions_with_all(tags)
+        .await
+        .map_err(|e| ApiError::internal(format!("Failed to query session tags: {e}")))?;
+    sessions.retain(|session| tagged.contains(&session.id));
+    Ok(())
+}
+
 async fn resolve_session_target(
     state: &AppState,
     user_id: &str,
@@ -388,6 +434,28 @@ fn merge_duplicate_sessions(mut sessions: Vec<ChatSession>) -> Vec<ChatSession>
     merged
 }
 
+/// Runner hosting `session_id` for `user_id`.
+pub(super) async fn session_runner(
+    state: &AppState,
+    user_id: &str,
+    session_id: &str,
+    shared_workspace_id: Option<&str>,
+) -> ApiResult<oqto_runner::client::RunnerClient> {
+    let target = resolve_session_target(
+        state,
+        user_id,
+        session_id,
+        shared_workspace_id,
+        is_multi_user_mode(state),
+    )
+    .await?;
+
+    resolve_runner_for_target(state, user_id, &target)
+        .await
+        .map_err(|e| ApiError::internal(format!("runner target resolution: {}", e)))?
+        .ok_or_else(|| ApiError::internal("Runner is required but not available for this user."))
+}
+
 /// List all chat sessions from hstry.
 ///
 /// In multi-user mode, this uses the runner to query hstry for the user.
@@ -430,7 +498,11 @@ pub async fn list_chat_history(
     };
 
     let response = runner
-        .list_workspace_chat_sessions(effective_workspace, query.include_children, query.limit)
+        .list_workspace_chat_sessions(
+            effective_workspace,
+            query.include_children,
+            query.runner_limit(),
+        )
         .await
         .map_err(|e| ApiError::internal(format!("runner list sessions failed: {}", e)))?;
 
@@ -472,6 +544,8 @@ pub async fn list_chat_history(
         sessions.retain(|s| !s.is_child);
     }
 
+    retain_sessions_with_tags(&state, &mut sessions, &query.tag_filter()).await?;
+
     sessions.sort_by_key(|s| Reverse(s.updated_at));
 
     if let Some(limit) = query.limit {
@@ -730,7 +804,11 @@ pub async fn list_chat_history_grouped(
     };
 
     let response = runner
-        .list_workspace_chat_sessions(effective_workspace, query.include_children, query.limit)
+        .list_workspace_chat_sessions(
+            effective_workspace,
+            query.include_children,
+            query.runner_limit(),
+        )
         .await
         .map_err(|e| ApiError::internal(format!("runner grouped list failed: {}", e)))?;
 
@@ -768,6 +846,8 @@ pub async fn list_chat_history_grouped(
         sessions.retain(|s| !s.is_child);
     }
 
+    retain_sessions_with_tags(&state, &mut sessions, &query.tag_filter()).await?;
+
     let mut grouped: std::collections::HashMap<String, Vec<ChatSession>> =
         std::collections::HashMap::new();
     for session in sessions {
@@ -870,6 +950,8 @@ fn convert_runner_response(
 ///
 /// SECURITY: In multi-user mode, we MUST use the runner to ensure user isolation.
 /// When shared_workspace_id is set, we verify the user is a member before routing.
+/// Messages the session owner made private come back as placeholders with
+/// `metadata.elided` to everyone else.
 #[instrument(skip(state))]
 pub async fn get_chat_messages(
     State(state): State<AppState>,
@@ -915,7 +997,7 @@ pub async fn get_chat_messages(
         .map_err(|e| ApiError::internal(format!("runner target resolution: {}", e)))?
         .ok_or_else(|| ApiError::internal("Runner is required but not available for this user."))?;
 
-    let response = runner
+    let mut response = runner
         .get_workspace_chat_session_messages(
             &resolved_session_id,
             query.render,
@@ -924,8 +1006,17 @@ pub async fn get_chat_messages(
         )
         .await
         .map_err(|e| ApiError::internal(format!("runner get messages failed: {}", e)))?;
-
-    let canonical = convert_runner_response(response);
+    let hidden =
+        super::private_messages::hidden_from(&state, &resolved_session_id, user.id()).await?;
+    crate::private_messages::elide(&mut response.messages, &hidden);
+
+    let mut canonical = convert_runner_response(response);
+    for message in canonical
+        .iter_mut()
+        .filter(|m| crate::private_messages::is_hidden(&hidden, &m.id))
+    {
+        message.metadata = Some(serde_json::json!({ "elided": true }));
+    }
 
     info!(
         user_id = %user.id(),
@@ -937,6 +1028,388 @@ pub async fn get_chat_messages(
     Ok(Json(canonical))
 }
 
+/// Query parameters for the long-poll events endpoint.
+#[derive(Debug, Deserialize)]
+pub struct PollSessionEventsQuery {
+    /// `next_cursor` from the previous poll. Omit to start with the oldest
+    /// buffered event.
+    pub cursor: Option<u64>,
+    /// How long to wait for new events: seconds, or with an `s`/`ms`/`m` unit.
+    pub wait: Option<String>,
+    /// If set, route the request to the shared workspace's runner instead of the personal runner.
+    pub shared_workspace_id: Option<String>,
+}
+
+/// Long-poll a session's canonical events.
+///
+/// For clients that cannot hold a WebSocket or SSE connection. Returns as
+/// soon as events after `cursor` are buffered, or an empty batch once `wait`
+/// elapses. The first poll for a session subscribes to it on the runner.
+#[instrument(skip(state))]
+pub async fn poll_session_events(
+    State(state): State<AppState>,
+    user: CurrentUser,
+    Path(session_id): Path<String>,
+    Query(query): Query<PollSessionEventsQuery>,
+) -> ApiResult<Json<crate::session_events::PollResponse>> {
+    let feeds = state
+        .session_events
+        .clone()
+        .ok_or_else(|| ApiError::not_found("Long-poll events are disabled"))?;
+    let wait = match query.wait.as_deref() {
+        Some(value) => Some(crate::session_events::parse_wait(value).ok_or_else(|| {
+            ApiError::bad_request(format!("invalid wait '{value}' (use e.g. 25s or 500ms)"))
+        })?),
+        None => None,
+    };
+
+    let runner = session_runner(
+        &state,
+        user.id(),
+        &session_id,
+        query.shared_workspace_id.as_deref(),
+    )
+    .await?;
+
+    let mut response = feeds
+        .poll(&runner, &session_id, query.cursor, feeds.wait_for(wait))
+        .await
+        .map_err(|e| ApiError::not_found(format!("session events unavailable: {}", e)))?;
+
+    // The buffer is shared by everyone polling the session, so what the
+    // owner made private is dropped per caller.
+    let hidden = super::private_messages::hidden_from(&state, &session_id, user.id()).await?;
+    if !hidden.is_empty() {
+        let tool_calls =
+            super::private_messages::tool_calls_in(&runner, &session_id, &hidden).await?;
+        crate::private_messages::drop_hidden_events(
+            &mut response.batch.events,
+            &hidden,
+            &tool_calls,
+        );
+    }
+
+    debug!(
+        user_id = %user.id(),
+        session_id = %session_id,
+        count = response.batch.events.len(),
+        next_cursor = response.batch.next_cursor,
+        "Long-poll session events"
+    );
+    Ok(Json(response))
+}
+
+/// Query parameters for session artifact endpoints.
+#[derive(Debug, Deserialize)]
+pub struct SessionArtifactQuery {
+    /// If set, route the request to the shared workspace's runner instead of the personal runner.
+    pub shared_workspace_id: Option<String>,
+    /// Serve images as downloads too (other files always are).
+    #[serde(default)]
+    pub download: bool,
+}
+
+/// List the files the agent produced in a session: images and deliverables
+/// from its artifacts directory, and workspace files matching the runner's
+/// artifact globs.
+///
+/// Lets clients restore inline images after a reload and offer results for
+/// download after the session is gone; live clients get them from
+/// `artifact.created` events.
+#[instrument(skip(state))]
+pub async fn list_session_artifacts(
+    State(state): State<AppState>,
+    user: CurrentUser,
+    Path(session_id): Path<String>,
+    Query(query): Query<SessionArtifactQuery>,
+) -> ApiResult<Json<Vec<oqto_protocol::events::MediaArtifact>>> {
+    let runner = session_runner(
+        &state,
+        user.id(),
+        &session_id,
+        query.shared_workspace_id.as_deref(),
+    )
+    .await?;
+    let mut artifacts = runner
+        .list_artifacts(&session_id)
+        .await
+        .map_err(|e| ApiError::internal(format!("runner list artifacts failed: {}", e)))?
+        .artifacts;
+    let hidden =
+        super::private_messages::hidden_tool_calls(&state, &runner, &session_id, user.id()).await?;
+    artifacts.retain(|artifact| {
+        !crate::private_messages::is_attachment_hidden(artifact.tool_call_id.as_deref(), &hidden)
+    });
+    Ok(Json(artifacts))
+}
+
+/// Serve a captured session artifact.
+#[instrument(skip(state))]
+pub async fn get_session_artifact(
+    State(state): State<AppState>,
+    user: CurrentUser,
+    Path((session_id, artifact_id)): Path<(String, String)>,
+    Query(query): Query<SessionArtifactQuery>,
+) -> ApiResult<axum::response::Response> {
+    serve_session_artifact(state, user, session_id, artifact_id, query, false).await
+}
+
+/// Serve a captured session artifact's thumbnail.
+#[instrument(skip(state))]
+pub async fn get_session_artifact_thumbnail(
+    State(state): State<AppState>,
+    user: CurrentUser,
+    Path((session_id, artifact_id)): Path<(String, String)>,
+    Query(query): Query<SessionArtifactQuery>,
+) -> ApiResult<axum::response::Response> {
+    serve_session_artifact(state, user, session_id, artifact_id, query, true).await
+}
+
+async fn serve_session_artifact(
+    state: AppState,
+    user: CurrentUser,
+    session_id: String,
+    artifact_id: String,
+    query: SessionArtifactQuery,
+    thumbnail: bool,
+) -> ApiResult<axum::response::Response> {
+    use axum::http::header;
+    use axum::response::IntoResponse;
+    use base64::Engine;
+
+    let runner = session_runner(
+        &state,
+        user.id(),
+        &session_id,
+        query.shared_workspace_id.as_deref(),
+    )
+    .await?;
+    let content = runner
+        .get_artifact(&artifact_id, thumbnail)
+        .await
+        .map_err(|e| ApiError::not_found(format!("Artifact unavailable: {e:#}")))?;
+    // Artifact IDs are runner-wide; a shared workspace runner holds several
+    // users' sessions. Files of private tool calls look absent too.
+    let hidden =
+        super::private_messages::hidden_tool_calls(&state, &runner, &session_id, user.id()).await?;
+    if content.session_id != session_id
+        || crate::private_messages::is_attachment_hidden(
+            content.artifact.tool_call_id.as_deref(),
+            &hidden,
+        )
+    {
+        return Err(ApiError::not_found(format!(
+            "Artifact {artifact_id} not found"
+        )));
+    }
+    let body = base64::engine::general_purpose::STANDARD
+        .decode(content.data_base64.as_bytes())
+        .map_err(|e| ApiError::internal(format!("invalid artifact payload: {e}")))?;
+    let disposition = if query.download || !content.mime_type.starts_with("image/") {
+        let file_name: String = content
+            .artifact
+            .filename
+            .chars()
+            .map(|c| {
+                if (c.is_ascii_graphic() && c != '"' && c != '\\') || c == ' ' {
+                    c
+                } else {
+                    '_'
+                }
+            })
+            .collect();
+        format!("attachment; filename=\"{file_name}\"")
+    } else {
+        "inline".to_string()
+    };
+
+    Ok((
+        [
+            (header::CONTENT_TYPE, content.mime_type),
+            (header::CONTENT_DISPOSITION, disposition),
+            // Captured artifacts never change.
+            (
+                header::CACHE_CONTROL,
+                "private, max-age=31536000, immutable".to_string(),
+            ),
+            (header::X_CONTENT_TYPE_OPTIONS, "nosniff".to_string()),
+            // SVGs are agent-generated; never let them run scripts.
+            (
+                header::CONTENT_SECURITY_POLICY,
+                "default-src 'none'; style-src 'unsafe-inline'; sandbox".to_string(),
+            ),
+        ],
+        body,
+    )
+        .into_response())
+}
+
+/// Request body for starting a background process.
+#[derive(Debug, Deserialize)]
+pub struct StartBackgroundProcessBody {
+    /// Program and arguments, e.g. `["npm", "run", "dev"]`.
+    pub command: Vec<String>,
+    #[serde(default)]
+    pub name: Option<String>,
+    /// Directory inside the session's working directory.
+    #[serde(default)]
+    pub cwd: Option<String>,
+    #[serde(default)]
+    pub env: HashMap<String, String>,
+    #[serde(default)]
+    pub restart: oqto_runner::protocol::RestartPolicy,
+}
+
+/// Query parameters for background process logs.
+#[derive(Debug, Deserialize)]
+pub struct BackgroundProcessLogsQuery {
+    /// Most recent lines to return.
+    pub lines: Option<usize>,
+    /// If set, route the request to the shared workspace's runner instead of the personal runner.
+    pub shared_workspace_id: Option<String>,
+}
+
+/// List a session's background processes (dev servers, watchers).
+#[instrument(skip(state))]
+pub async fn list_background_processes(
+    State(state): State<AppState>,
+    user: CurrentUser,
+    Path(session_id): Path<String>,
+    Query(query): Query<SessionArtifactQuery>,
+) -> ApiResult<Json<Vec<oqto_runner::protocol::BackgroundProcessInfo>>> {
+    let runner = session_runner(
+        &state,
+        user.id(),
+        &session_id,
+        query.shared_workspace_id.as_deref(),
+    )
+    .await?;
+    let response = runner
+        .list_background_processes(Some(&session_id))
+        .await
+        .map_err(|e| {
+            ApiError::internal(format!("runner list background processes failed: {e:#}"))
+        })?;
+    Ok(Json(response.processes))
+}
+
+/// Start a supervised background process for a session.
+///
+/// Agents call this through `oqtoctl bg start`. The runner keeps the process
+/// running across turns, optionally restarts it after crashes, and stops it
+/// when the session is closed.
+#[instrument(skip(state, body))]
+pub async fn start_background_process(
+    State(state): State<AppState>,
+    user: CurrentUser,
+    Path(session_id): Path<String>,
+    Query(query): Query<SessionArtifactQuery>,
+    Json(body): Json<StartBackgroundProcessBody>,
+) -> ApiResult<(
+    StatusCode,
+    Json<oqto_runner::protocol::BackgroundProcessInfo>,
+)> {
+    if body.command.is_empty() {
+        return Err(ApiError::bad_request("command must not be empty"));
+    }
+    let runner = session_runner(
+        &state,
+        user.id(),
+        &session_id,
+        query.shared_workspace_id.as_deref(),
+    )
+    .await?;
+    let process = runner
+        .start_background_process(oqto_runner::protocol::StartBackgroundProcessRequest {
+            session_id: session_id.clone(),
+            name: body.name,
+            command: body.command,
+            cwd: body.cwd.map(std::path::PathBuf::from),
+            env: body.env,
+            restart: body.restart,
+        })
+        .await
+        .map_err(|e| ApiError::bad_request(format!("Failed to start background process: {e:#}")))?;
+
+    info!(
+        user_id = %user.id(),
+        session_id = %session_id,
+        process_id = %process.id,
+        "Started background process"
+    );
+    Ok((StatusCode::CREATED, Json(process)))
+}
+
+/// Stop one of a session's background processes.
+#[instrument(skip(state))]
+pub async fn stop_background_process(
+    State(state): State<AppState>,
+    user: CurrentUser,
+    Path((session_id, process_id)): Path<(String, String)>,
+    Query(query): Query<SessionArtifactQuery>,
+) -> ApiResult<Json<oqto_runner::protocol::BackgroundProcessInfo>> {
+    let runner = session_runner(
+        &state,
+        user.id(),
+        &session_id,
+        query.shared_workspace_id.as_deref(),
+    )
+    .await?;
+    ensure_session_process(&runner, &session_id, &process_id).await?;
+    let process = runner
+        .stop_background_process(&process_id)
+        .await
+        .map_err(|e| ApiError::internal(format!("Failed to stop background process: {e:#}")))?;
+    Ok(Json(process))
+}
+
+/// Recent output (stdout and stderr) of a session's background process.
+#[instrument(skip(state))]
+pub async fn get_background_process_logs(
+    State(state): State<AppState>,
+    user: CurrentUser,
+    Path((session_id, process_id)): Path<(String, String)>,
+    Query(query): Query<BackgroundProcessLogsQuery>,
+) -> ApiResult<Json<oqto_runner::protocol::BackgroundProcessLogsResponse>> {
+    let runner = session_runner(
+        &state,
+        user.id(),
+        &session_id,
+        query.shared_workspace_id.as_deref(),
+    )
+    .await?;
+    ensure_session_process(&runner, &session_id, &process_id).await?;
+    let logs = runner
+        .get_background_process_logs(&process_id, query.lines)
+        .await
+        .map_err(|e| {
+            ApiError::internal(format!("Failed to read background process logs: {e:#}"))
+        })?;
+    Ok(Json(logs))
+}
+
+/// Background process IDs are runner-wide; a shared workspace runner holds
+/// several users' sessions.
+async fn ensure_session_process(
+    runner: &oqto_runner::client::RunnerClient,
+    session_id: &str,
+    process_id: &str,
+) -> ApiResult<()> {
+    let listed = runner
+        .list_background_processes(Some(session_id))
+        .await
+        .map_err(|e| {
+            ApiError::internal(format!("runner list background processes failed: {e:#}"))
+        })?;
+    if listed.processes.iter().any(|p| p.id == process_id) {
+        Ok(())
+    } else {
+        Err(ApiError::not_found(format!(
+            "Background process {process_id} not found"
+        )))
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::merge_duplicate_sessions;
@@ -0,0 +1,268 @@
+//! Read-only SQL queries over workspace data files.
+//!
+//! `POST /workspaces/{id}/query` (`{id}` is the URL-encoded workspace path)
+//! has the runner run DuckDB as the workspace's user over CSV, Parquet, JSON
+//! or SQLite files in the workspace, capped in rows, output size and time,
+//! so agents and users can look into large data files without downloading
+//! them. Results go out as NDJSON or CSV. The runner answers with the whole
+//! result, so nothing is sent before the query has finished; the caps keep
+//! that buffer small.
+
+use std::convert::Infallible;
+
+use axum::{
+    Json,
+    body::Body,
+    extract::{Path, State},
+    http::header,
+    response::{IntoResponse, Response},
+};
+use serde::Deserialize;
+use serde_json::{Value, json};
+use tracing::{info, instrument};
+
+use oqto_runner::client::RunnerClient;
+use oqto_runner::protocol::{DataQueryRequest, DataQueryResponse, ErrorCode, ErrorResponse};
+
+use crate::auth::CurrentUser;
+use crate::shared_workspace::SharePermission;
+
+use super::trx::validated_runner;
+use crate::api::error::{ApiError, ApiResult};
+use crate::api::state::AppState;
+
+/// Response header telling whether the result was cut at the row or size
+/// limit.
+const TRUNCATED_HEADER: &str = "x-oqto-query-truncated";
+/// Response header carrying how long the query took (ms).
+const ELAPSED_HEADER: &str = "x-oqto-query-elapsed-ms";
+
+/// How the result is encoded.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DataQueryFormat {
+    /// A `{"columns": [...]}` line, one JSON array per row, then a
+    /// `{"row_count", "truncated", "elapsed_ms"}` line.
+    #[default]
+    Ndjson,
+    /// A header row with the column names, then one record per row.
+    Csv,
+}
+
+/// Body of `POST /workspaces/{id}/query`.
+#[derive(Debug, Deserialize)]
+pub struct DataQueryBody {
+    /// A single query; relative file paths resolve against the workspace.
+    pub sql: String,
+    /// Most rows returned (default 1000, at most 100000).
+    #[serde(default)]
+    pub max_rows: Option<usize>,
+    /// Time limit (default 30s, at most 300s).
+    #[serde(default)]
+    pub timeout_secs: Option<u64>,
+    #[serde(default)]
+    pub format: DataQueryFormat,
+}
+
+/// Resolve the workspace and check that the caller may read its files.
+async fn query_runner(
+    state: &AppState,
+    user_id: &str,
+    workspace_path: &str,
+) -> ApiResult<(std::path::PathBuf, RunnerClient)> {
+    let (canonical, runner) = validated_runner(state, user_id, workspace_path).await?;
+    if let Some(sw_service) = state.shared_workspaces.as_ref()
+        && let Some(permissions) = sw_service
+            .permissions_for_path(&canonical.display().to_string(), user_id)
+            .await?
+        && !permissions.allows(SharePermission::FileRead)
+    {
+        return Err(ApiError::forbidden(format!(
+            "Missing '{}' permission in this workspace",
+            SharePermission::FileRead
+        )));
+    }
+    Ok((canonical, runner))
+}
+
+fn query_error(err: anyhow::Error) -> ApiError {
+    match err.downcast_ref::<ErrorResponse>() {
+        Some(e) => match e.code {
+            ErrorCode::InvalidRequest
+            | ErrorCode::DataQueryFailed
+            | ErrorCode::DataQueryTimeout => ApiError::bad_request(e.message.clone()),
+            ErrorCode::DataQueryUnavailable => {
+                ApiError::service_unavailable("Data queries need duckdb installed on the runner")
+            }
+            ErrorCode::PathNotFound | ErrorCode::NotADirectory => {
+                ApiError::not_found(e.message.clone())
+            }
+            _ => ApiError::internal(format!("Data query failed: {}", e.message)),
+        },
+        None => ApiError::internal(format!("Data query failed: {err:#}")),
+    }
+}
+
+/// A value as a CSV cell: strings as they are, NULL as an empty cell.
+fn csv_cell(value: &Value) -> String {
+    match value {
+        Value::Null => String::new(),
+        Value::String(s) => s.clone(),
+        other => other.to_string(),
+    }
+}
+
+/// Encode the result line by line; rows are serialized as the body is
+/// sent.
+fn result_lines(
+    result: DataQueryResponse,
+    format: DataQueryFormat,
+) -> impl Iterator<Item = String> + Send {
+    let DataQueryResponse {
+        columns,
+        rows,
+        truncated,
+        elapsed_ms,
+    } = result;
+    let row_count = rows.len();
+    let (header, trailer) = match format {
+        DataQueryFormat::Ndjson => (
+            format!("{}\n", json!({ "columns": columns })),
+            Some(format!(
+                "{}\n",
+                json!({
+                    "row_count": row_count,
+                    "truncated": truncated,
+                    "elapsed_ms": elapsed_ms,
+                })
+            )),
+        ),
+        DataQueryFormat::Csv => {
+            let mut header = String::new();
+            crate::invite::csv::write_record(&mut header, columns.iter().map(|c| c.name.as_str()));
+            (header, None)
+        }
+    };
+    let rows = rows.into_iter().map(move |row| match format {
+        DataQueryFormat::Ndjson => format!("{}\n", Value::Array(row)),
+        DataQueryFormat::Csv => {
+            let cells: Vec<String> = row.iter().map(csv_cell).collect();
+            let mut line = String::new();
+            crate::invite::csv::write_record(&mut line, cells.iter().map(String::as_str));
+            line
+        }
+    });
+    std::iter::once(header).chain(rows).chain(trailer)
+}
+
+/// Run a read-only SQL query over files in the workspace.
+#[instrument(skip(state, user, request))]
+pub async fn query_workspace_data(
+    State(state): State<AppState>,
+    user: CurrentUser,
+    Path(id): Path<String>,
+    Json(request): Json<DataQueryBody>,
+) -> ApiResult<Response> {
+    if request.sql.trim().is_empty() {
+        return Err(ApiError::bad_request("sql is required"));
+    }
+    let (canonical, runner) = query_runner(&state, user.id(), &id).await?;
+    let result = runner
+        .query_data(DataQueryRequest {
+            workspace_path: canonical.clone(),
+            sql: request.sql,
+            max_rows: request.max_rows,
+            timeout_secs: request.timeout_secs,
+        })
+        .await
+        .map_err(query_error)?;
+    info!(
+        user_id = %user.id(),
+        workspace = %canonical.display(),
+        rows = result.rows.len(),
+        truncated = result.truncated,
+        elapsed_ms = result.elapsed_ms,
+        "Ran workspace data query"
+    );
+
+    let content_type = match request.format {
+        DataQueryFormat::Ndjson => "application/x-ndjson",
+        DataQueryFormat::Csv => "text/csv; charset=utf-8",
+    };
+    let truncated = result.truncated.to_string();
+    let elapsed_ms = result.elapsed_ms.to_string();
+    let lines = result_lines(result, request.format).map(Ok::<_, Infallible>);
+    Ok((
+        [
+            (header::CONTENT_TYPE.as_str(), content_type.to_string()),
+            (TRUNCATED_HEADER, truncated),
+            (ELAPSED_HEADER, elapsed_ms),
+        ],
+        Body::from_stream(futures::stream::iter(lines)),
+    )
+        .into_response())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use oqto_runner::protocol::DataQueryColumn;
+
+    fn result() -> DataQueryResponse {
+        DataQueryResponse {
+            columns: vec![
+                DataQueryColumn {
+                    name: "id".to_string(),
+                    data_type: "BIGINT".to_string(),
+                },
+                DataQueryColumn {
+                    name: "note".to_string(),
+                    data_type: "VARCHAR".to_string(),
+                },
+            ],
+            rows: vec![
+                vec![json!(1), json!("plain")],
+                vec![json!(2), json!("a, \"quoted\" one")],
+                vec![json!(3), Value::Null],
+            ],
+            truncated: true,
+            elapsed_ms: 12,
+        }
+    }
+
+    #[test]
+    fn ndjson_has_columns_rows_and_trailer() {
+        let lines: Vec<String> = result_lines(result(), DataQueryFormat::Ndjson).collect();
+        assert_eq!(lines.len(), 5);
+        let header: Value = serde_json::from_str(&lines[0]).unwrap();
+        assert_eq!(header["columns"][1]["type"], "VARCHAR");
+        assert_eq!(lines[3], "[3,null]\n");
+        let trailer: Value = serde_json::from_str(&lines[4]).unwrap();
+        assert_eq!(trailer["row_count"], 3);
+        assert_eq!(trailer["truncated"], true);
+    }
+
+    #[test]
+    fn csv_quotes_cells_and_leaves_nulls_empty() {
+        let csv: String = result_lines(result(), DataQueryFormat::Csv).collect();
+        assert_eq!(csv, "id,note\n1,plain\n2,\"a, \"\"quoted\"\" one\"\n3,\n");
+    }
+
+    #[test]
+    fn runner_errors_map_to_statuses() {
+        let runner_error = |code, message: &str| {
+            anyhow::Error::from(ErrorResponse {
+                code,
+                message: message.to_string(),
+            })
+        };
+        assert!(matches!(
+            query_error(runner_error(ErrorCode::DataQueryFailed, "Binder Error")),
+            ApiError::BadRequest(_)
+        ));
+        assert!(matches!(
+            query_error(runner_error(ErrorCode::DataQueryUnavailable, "x")),
+            ApiError::ServiceUnavailable(_)
+        ));
+    }
+}
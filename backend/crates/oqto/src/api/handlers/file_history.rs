@@ -0,0 +1,211 @@
+//! Workspace file history handlers.
+//!
+//! The runner snapshots a workspace at the start and end of every agent
+//! turn; these endpoints list a file's snapshots (and commits of the
+//! workspace's own git repository) and serve its content at one of them.
+
+use std::path::{Component, Path as FsPath};
+
+use axum::{
+    Json,
+    extract::{Path, Query, State},
+    http::header,
+    response::{IntoResponse, Response},
+};
+use base64::Engine;
+use serde::Deserialize;
+use tracing::instrument;
+
+use oqto_runner::client::RunnerClient;
+use oqto_runner::protocol::{FileVersionSelector, FileVersionsResponse};
+
+use crate::auth::CurrentUser;
+use crate::shared_workspace::SharePermission;
+
+use super::trx::validated_runner;
+use crate::api::error::{ApiError, ApiResult};
+use crate::api::state::AppState;
+
+/// Response header carrying the served version's id (`current` for the
+/// file as it is now).
+const VERSION_HEADER: &str = "x-oqto-version";
+/// Response header carrying when the served version was recorded (Unix ms).
+const VERSION_TIMESTAMP_HEADER: &str = "x-oqto-version-timestamp";
+
+/// Query for `GET /workspaces/{id}/files/{*path}`.
+#[derive(Debug, Deserialize)]
+pub struct FileAtQuery {
+    /// RFC 3339 or Unix-millisecond timestamp, or a version id / commit
+    /// from the version list. Omitted: the current content.
+    #[serde(default)]
+    pub at: Option<String>,
+}
+
+/// Query for `GET /workspaces/{id}/file-versions/{*path}`.
+#[derive(Debug, Deserialize)]
+pub struct FileVersionsQuery {
+    #[serde(default)]
+    pub limit: Option<usize>,
+}
+
+/// Interpret `at`: timestamps select the newest version recorded at or
+/// before them, anything else names a version.
+fn parse_selector(at: &str) -> Result<FileVersionSelector, ApiError> {
+    let at = at.trim();
+    if at.is_empty() {
+        return Err(ApiError::bad_request("at must not be empty"));
+    }
+    if let Ok(time) = chrono::DateTime::parse_from_rfc3339(at) {
+        return Ok(FileVersionSelector::At(time.timestamp_millis()));
+    }
+    if at.bytes().all(|b| b.is_ascii_digit()) {
+        let ms = at
+            .parse()
+            .map_err(|_| ApiError::bad_request("at is out of range"))?;
+        return Ok(FileVersionSelector::At(ms));
+    }
+    Ok(FileVersionSelector::Version(at.to_string()))
+}
+
+/// Reject absolute paths and `..` segments.
+fn relative_file_path(path: &str) -> Result<&str, ApiError> {
+    let valid = !path.is_empty()
+        && FsPath::new(path)
+            .components()
+            .all(|c| matches!(c, Component::Normal(_) | Component::CurDir));
+    if !valid {
+        return Err(ApiError::bad_request(
+            "File path must be relative to the workspace",
+        ));
+    }
+    Ok(path)
+}
+
+/// Resolve the workspace (`{id}` is its URL-encoded path) and check that the
+/// caller may read its files.
+async fn file_runner(
+    state: &AppState,
+    user_id: &str,
+    workspace_path: &str,
+) -> ApiResult<(std::path::PathBuf, RunnerClient)> {
+    let (canonical, runner) = validated_runner(state, user_id, workspace_path).await?;
+    if let Some(sw_service) = state.shared_workspaces.as_ref()
+        && let Some(permissions) = sw_service
+            .permissions_for_path(&canonical.display().to_string(), user_id)
+            .await?
+        && !permissions.allows(SharePermission::FileRead)
+    {
+        return Err(ApiError::forbidden(format!(
+            "Missing '{}' permission in this workspace",
+            SharePermission::FileRead
+        )));
+    }
+    Ok((canonical, runner))
+}
+
+/// Versions of a workspace file, newest first.
+#[instrument(skip(state, user))]
+pub async fn list_workspace_file_versions(
+    State(state): State<AppState>,
+    user: CurrentUser,
+    Path((id, path)): Path<(String, String)>,
+    Query(query): Query<FileVersionsQuery>,
+) -> ApiResult<Json<FileVersionsResponse>> {
+    let path = relative_file_path(&path)?;
+    let (canonical, runner) = file_runner(&state, user.id(), &id).await?;
+    let versions = runner
+        .list_file_versions(canonical, path, query.limit)
+        .await
+        .map_err(|e| ApiError::bad_request(format!("Listing file versions failed: {e:#}")))?;
+    Ok(Json(versions))
+}
+
+/// Serve a workspace file, optionally as it was at an earlier version.
+#[instrument(skip(state, user))]
+pub async fn get_workspace_file(
+    State(state): State<AppState>,
+    user: CurrentUser,
+    Path((id, path)): Path<(String, String)>,
+    Query(query): Query<FileAtQuery>,
+) -> ApiResult<Response> {
+    let path = relative_file_path(&path)?;
+    let selector = query.at.as_deref().map(parse_selector).transpose()?;
+    let (canonical, runner) = file_runner(&state, user.id(), &id).await?;
+
+    let (content_base64, version, timestamp) = match selector {
+        Some(selector) => {
+            let content = runner
+                .read_file_version(&canonical, path, selector)
+                .await
+                .map_err(|e| ApiError::not_found(format!("File version unavailable: {e:#}")))?;
+            (
+                content.content_base64,
+                content.version.id,
+                Some(content.version.timestamp),
+            )
+        }
+        None => {
+            let content = runner
+                .read_file(canonical.join(path), None, None)
+                .await
+                .map_err(|e| ApiError::not_found(format!("File unavailable: {e:#}")))?;
+            (content.content_base64, "current".to_string(), None)
+        }
+    };
+    let body = base64::engine::general_purpose::STANDARD
+        .decode(content_base64.as_bytes())
+        .map_err(|e| ApiError::internal(format!("invalid file payload: {e}")))?;
+
+    let content_type = if std::str::from_utf8(&body).is_ok() {
+        "text/plain; charset=utf-8"
+    } else {
+        "application/octet-stream"
+    };
+    let mut response = (
+        [
+            (header::CONTENT_TYPE, content_type.to_string()),
+            (header::X_CONTENT_TYPE_OPTIONS, "nosniff".to_string()),
+            (header::CACHE_CONTROL, "private, no-cache".to_string()),
+            (header::HeaderName::from_static(VERSION_HEADER), version),
+        ],
+        body,
+    )
+        .into_response();
+    if let Some(timestamp) = timestamp {
+        response.headers_mut().insert(
+            VERSION_TIMESTAMP_HEADER,
+            header::HeaderValue::from(timestamp),
+        );
+    }
+    Ok(response)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn at_accepts_timestamps_and_versions() {
+        assert_eq!(
+            parse_selector("2026-05-01T12:00:00Z").unwrap(),
+            FileVersionSelector::At(1_777_636_800_000)
+        );
+        assert_eq!(
+            parse_selector("1777636800000").unwrap(),
+            FileVersionSelector::At(1_777_636_800_000)
+        );
+        assert_eq!(
+            parse_selector("snapshot:3f2a9c1").unwrap(),
+            FileVersionSelector::Version("snapshot:3f2a9c1".to_string())
+        );
+        assert!(parse_selector(" ").is_err());
+    }
+
+    #[test]
+    fn file_paths_stay_inside_the_workspace() {
+        assert!(relative_file_path("src/main.rs").is_ok());
+        assert!(relative_file_path("../etc/passwd").is_err());
+        assert!(relative_file_path("/etc/passwd").is_err());
+        assert!(relative_file_path("").is_err());
+    }
+}
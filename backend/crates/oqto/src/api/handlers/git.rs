@@ -0,0 +1,348 @@
+//! Git handlers for workspace repositories.
+//!
+//! The runner shells out to `git` in the workspace (`{id}` is its
+//! URL-encoded path), so the frontend can show status, diffs and history of
+//! agent changes and commit or push them without a terminal. Pushes use the
+//! caller's stored HTTPS credentials for the remote's host.
+
+use axum::{
+    Json,
+    extract::{Path, Query, State},
+    http::StatusCode,
+};
+use serde::Deserialize;
+use tracing::{info, instrument};
+
+use oqto_runner::client::RunnerClient;
+use oqto_runner::protocol::{
+    ErrorCode, ErrorResponse, GitBranchesResponse, GitCommitInfo, GitCommitRequest,
+    GitDiffResponse, GitLogResponse, GitPushRequest, GitPushResponse, GitStatusResponse,
+};
+
+use crate::auth::CurrentUser;
+use crate::git_credentials::{
+    GitCredentialInfo, GitCredentialRepository, SetGitCredentialRequest, normalize_host,
+};
+use crate::shared_workspace::SharePermission;
+
+use super::trx::validated_runner;
+use crate::api::error::{ApiError, ApiResult};
+use crate::api::state::AppState;
+
+/// Query for `GET /workspaces/{id}/git/diff`.
+#[derive(Debug, Deserialize)]
+pub struct GitDiffQuery {
+    /// Limit the diff to this path, relative to the workspace.
+    #[serde(default)]
+    pub path: Option<String>,
+    /// Only the staged changes.
+    #[serde(default)]
+    pub staged: bool,
+    /// The changes made by this commit instead of the uncommitted ones.
+    #[serde(default)]
+    pub commit: Option<String>,
+}
+
+/// Query for `GET /workspaces/{id}/git/log`.
+#[derive(Debug, Deserialize)]
+pub struct GitLogQuery {
+    #[serde(default)]
+    pub limit: Option<usize>,
+    #[serde(default)]
+    pub path: Option<String>,
+    /// Start from this revision instead of HEAD.
+    #[serde(default)]
+    pub rev: Option<String>,
+}
+
+/// Body of `POST /workspaces/{id}/git/branches`.
+#[derive(Debug, Deserialize)]
+pub struct SwitchBranchRequest {
+    pub name: String,
+    /// Create the branch from HEAD first.
+    #[serde(default)]
+    pub create: bool,
+}
+
+/// Body of `POST /workspaces/{id}/git/commit`.
+#[derive(Debug, Deserialize)]
+pub struct CommitRequest {
+    pub message: String,
+    /// Paths to commit; every change (untracked files included) when omitted.
+    #[serde(default)]
+    pub paths: Option<Vec<String>>,
+    #[serde(default)]
+    pub author_name: Option<String>,
+    #[serde(default)]
+    pub author_email: Option<String>,
+}
+
+/// Body of `POST /workspaces/{id}/git/push`.
+#[derive(Debug, Default, Deserialize)]
+pub struct PushRequest {
+    #[serde(default)]
+    pub remote: Option<String>,
+    #[serde(default)]
+    pub branch: Option<String>,
+    #[serde(default)]
+    pub set_upstream: bool,
+}
+
+fn credentials(state: &AppState) -> ApiResult<&GitCredentialRepository> {
+    state
+        .git_credentials
+        .as_deref()
+        .ok_or_else(|| ApiError::not_found("Git credentials are not available"))
+}
+
+/// Resolve the workspace and check that the caller holds `permission` in it
+/// (reads need `file_read`, commits and pushes `file_write`).
+async fn git_runner(
+    state: &AppState,
+    user_id: &str,
+    workspace_path: &str,
+    permission: SharePermission,
+) -> ApiResult<(std::path::PathBuf, RunnerClient)> {
+    let (canonical, runner) = validated_runner(state, user_id, workspace_path).await?;
+    if let Some(sw_service) = state.shared_workspaces.as_ref()
+        && let Some(permissions) = sw_service
+            .permissions_for_path(&canonical.display().to_string(), user_id)
+            .await?
+        && !permissions.allows(permission)
+    {
+        return Err(ApiError::forbidden(format!(
+            "Missing '{permission}' permission in this workspace"
+        )));
+    }
+    Ok((canonical, runner))
+}
+
+fn git_error(err: anyhow::Error) -> ApiError {
+    match err.downcast_ref::<ErrorResponse>() {
+        Some(e) => match e.code {
+            ErrorCode::NotAGitRepository => {
+                ApiError::not_found("Workspace is not a git repository")
+            }
+            ErrorCode::PathNotFound => ApiError::not_found(e.message.clone()),
+            ErrorCode::GitFailed | ErrorCode::InvalidRequest => {
+                ApiError::bad_request(e.message.clone())
+            }
+            _ => ApiError::internal(format!("Git operation failed: {}", e.message)),
+        },
+        None => ApiError::internal(format!("Git operation failed: {err:#}")),
+    }
+}
+
+/// Branch, upstream and changed files of the workspace repository.
+#[instrument(skip(state, user))]
+pub async fn get_workspace_git_status(
+    State(state): State<AppState>,
+    user: CurrentUser,
+    Path(id): Path<String>,
+) -> ApiResult<Json<GitStatusResponse>> {
+    let (canonical, runner) = git_runner(&state, user.id(), &id, SharePermission::FileRead).await?;
+    Ok(Json(runner.git_status(canonical).await.map_err(git_error)?))
+}
+
+/// Unified diff of the uncommitted changes or of one commit.
+#[instrument(skip(state, user))]
+pub async fn get_workspace_git_diff(
+    State(state): State<AppState>,
+    user: CurrentUser,
+    Path(id): Path<String>,
+    Query(query): Query<GitDiffQuery>,
+) -> ApiResult<Json<GitDiffResponse>> {
+    let (canonical, runner) = git_runner(&state, user.id(), &id, SharePermission::FileRead).await?;
+    let diff = runner
+        .git_diff(canonical, query.path, query.staged, query.commit)
+        .await
+        .map_err(git_error)?;
+    Ok(Json(diff))
+}
+
+/// Commit history, newest first.
+#[instrument(skip(state, user))]
+pub async fn get_workspace_git_log(
+    State(state): State<AppState>,
+    user: CurrentUser,
+    Path(id): Path<String>,
+    Query(query): Query<GitLogQuery>,
+) -> ApiResult<Json<GitLogResponse>> {
+    let (canonical, runner) = git_runner(&state, user.id(), &id, SharePermission::FileRead).await?;
+    let log = runner
+        .git_log(canonical, query.limit, query.path, query.rev)
+        .await
+        .map_err(git_error)?;
+    Ok(Json(log))
+}
+
+/// Local branches.
+#[instrument(skip(state, user))]
+pub async fn list_workspace_git_branches(
+    State(state): State<AppState>,
+    user: CurrentUser,
+    Path(id): Path<String>,
+) -> ApiResult<Json<GitBranchesResponse>> {
+    let (canonical, runner) = git_runner(&state, user.id(), &id, SharePermission::FileRead).await?;
+    Ok(Json(
+        runner.git_branches(canonical).await.map_err(git_error)?,
+    ))
+}
+
+/// Check out a branch, optionally creating it.
+#[instrument(skip(state, user, request))]
+pub async fn switch_workspace_git_branch(
+    State(state): State<AppState>,
+    user: CurrentUser,
+    Path(id): Path<String>,
+    Json(request): Json<SwitchBranchRequest>,
+) -> ApiResult<Json<GitBranchesResponse>> {
+    let name = request.name.trim();
+    if name.is_empty() {
+        return Err(ApiError::bad_request("name must not be empty"));
+    }
+    let (canonical, runner) =
+        git_runner(&state, user.id(), &id, SharePermission::FileWrite).await?;
+    let branches = runner
+        .git_switch_branch(canonical, name, request.create)
+        .await
+        .map_err(git_error)?;
+    Ok(Json(branches))
+}
+
+/// Stage and commit changes.
+#[instrument(skip(state, user, request))]
+pub async fn commit_workspace_git(
+    State(state): State<AppState>,
+    user: CurrentUser,
+    Path(id): Path<String>,
+    Json(request): Json<CommitRequest>,
+) -> ApiResult<(StatusCode, Json<GitCommitInfo>)> {
+    if request.message.trim().is_empty() {
+        return Err(ApiError::bad_request("message must not be empty"));
+    }
+    if request.author_name.is_some() != request.author_email.is_some() {
+        return Err(ApiError::bad_request(
+            "author_name and author_email must be set together",
+        ));
+    }
+    let (canonical, runner) =
+        git_runner(&state, user.id(), &id, SharePermission::FileWrite).await?;
+    let commit = runner
+        .git_commit(GitCommitRequest {
+            workspace_path: canonical,
+            message: request.message,
+            paths: request.paths,
+            author_name: request.author_name,
+            author_email: request.author_email,
+        })
+        .await
+        .map_err(git_error)?;
+    info!(user_id = %user.id(), commit = %commit.commit, "workspace git commit");
+    Ok((StatusCode::CREATED, Json(commit)))
+}
+
+/// Push a branch with the caller's stored credentials.
+#[instrument(skip(state, user, request))]
+pub async fn push_workspace_git(
+    State(state): State<AppState>,
+    user: CurrentUser,
+    Path(id): Path<String>,
+    request: Option<Json<PushRequest>>,
+) -> ApiResult<Json<GitPushResponse>> {
+    let request = request.map(|Json(r)| r).unwrap_or_default();
+    let (canonical, runner) =
+        git_runner(&state, user.id(), &id, SharePermission::FileWrite).await?;
+    let credentials = match state.git_credentials.as_deref() {
+        Some(repo) => repo.for_push(user.id()).await?,
+        None => Vec::new(),
+    };
+    let pushed = runner
+        .git_push(GitPushRequest {
+            workspace_path: canonical,
+            remote: request.remote,
+            branch: request.branch,
+            set_upstream: request.set_upstream,
+            credentials,
+        })
+        .await
+        .map_err(git_error)?;
+    info!(
+        user_id = %user.id(),
+        remote = %pushed.remote,
+        branch = %pushed.branch,
+        "workspace git push"
+    );
+    Ok(Json(pushed))
+}
+
+/// Hosts the caller has stored credentials for (tokens are never returned).
+#[instrument(skip(state, user))]
+pub async fn list_git_credentials(
+    State(state): State<AppState>,
+    user: CurrentUser,
+) -> ApiResult<Json<Vec<GitCredentialInfo>>> {
+    Ok(Json(credentials(&state)?.list(user.id()).await?))
+}
+
+/// Store the caller's credential for a host, replacing an existing one.
+#[instrument(skip(state, user, request))]
+pub async fn set_git_credential(
+    State(state): State<AppState>,
+    user: CurrentUser,
+    Json(request): Json<SetGitCredentialRequest>,
+) -> ApiResult<StatusCode> {
+    let repo = credentials(&state)?;
+    let host = normalize_host(&request.host)
+        .ok_or_else(|| ApiError::bad_request("host must be a host name or HTTPS URL"))?;
+    if request.username.trim().is_empty() || request.token.is_empty() {
+        return Err(ApiError::bad_request(
+            "username and token must not be empty",
+        ));
+    }
+    repo.upsert(user.id(), &host, request.username.trim(), &request.token)
+        .await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Remove the caller's credential for a host.
+#[instrument(skip(state, user))]
+pub async fn delete_git_credential(
+    State(state): State<AppState>,
+    user: CurrentUser,
+    Path(host): Path<String>,
+) -> ApiResult<StatusCode> {
+    let repo = credentials(&state)?;
+    let host = normalize_host(&host).ok_or_else(|| ApiError::bad_request("invalid host"))?;
+    if !repo.delete(user.id(), &host).await? {
+        return Err(ApiError::not_found("No credential stored for this host"));
+    }
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn runner_errors_map_to_statuses() {
+        let runner_error = |code, message: &str| {
+            anyhow::Error::from(ErrorResponse {
+                code,
+                message: message.to_string(),
+            })
+        };
+        assert!(matches!(
+            git_error(runner_error(ErrorCode::NotAGitRepository, "x")),
+            ApiError::NotFound(_)
+        ));
+        assert!(matches!(
+            git_error(runner_error(ErrorCode::GitFailed, "nothing to commit")),
+            ApiError::BadRequest(_)
+        ));
+        assert!(matches!(
+            git_error(anyhow::anyhow!("connection closed")),
+            ApiError::Internal(_)
+        ));
+    }
+}
@@ -0,0 +1,189 @@
+//! Container image builds from a Dockerfile in a session's workspace.
+
+use std::path::PathBuf;
+use std::time::Duration;
+
+use axum::{
+    Json,
+    extract::{Path, State},
+    http::StatusCode,
+};
+use chrono::Utc;
+use oqto_protocol::events::{Event, EventPayload};
+use serde::{Deserialize, Serialize};
+use tokio::sync::mpsc;
+use tracing::{info, instrument, warn};
+
+use crate::auth::CurrentUser;
+use crate::container::ImageBuildSpec;
+use crate::session::image_builds;
+use crate::ws::types::WsEvent;
+
+use crate::api::error::{ApiError, ApiResult};
+use crate::api::state::AppState;
+
+/// Longest a build may run before it is killed.
+const BUILD_TIMEOUT: Duration = Duration::from_secs(60 * 60);
+
+/// Most output lines sent as events per build; the rest are dropped.
+const MAX_OUTPUT_LINES: usize = 20_000;
+
+#[derive(Debug, Deserialize)]
+pub struct BuildImageRequest {
+    /// Dockerfile relative to the workspace, which is the build context.
+    #[serde(default = "default_dockerfile")]
+    pub dockerfile: String,
+    /// Name in the image tag (default: the workspace directory name).
+    pub name: Option<String>,
+}
+
+fn default_dockerfile() -> String {
+    "Dockerfile".to_string()
+}
+
+#[derive(Debug, Serialize)]
+pub struct ImageBuildStarted {
+    pub build_id: String,
+    /// Tag of the image; pass it as `image` when creating a session.
+    pub image: String,
+}
+
+async fn send_event(state: &AppState, user_id: &str, session_id: &str, payload: EventPayload) {
+    let event = Event {
+        session_id: session_id.to_string(),
+        runner_id: "local".to_string(),
+        ts: Utc::now().timestamp_millis(),
+        seq: None,
+        payload,
+    };
+    state
+        .ws_hub
+        .send_to_user(
+            user_id,
+            WsEvent::AgentEvent {
+                session_id: session_id.to_string(),
+                event: serde_json::to_value(&event).unwrap_or_default(),
+            },
+        )
+        .await;
+}
+
+/// Build a container image from a Dockerfile in the session's workspace.
+///
+/// The build runs in the background; its output streams to the session as
+/// `image.build_output` events and ends with `image.build_finished`.
+#[instrument(skip(state, user, request))]
+pub async fn build_session_image(
+    State(state): State<AppState>,
+    user: CurrentUser,
+    Path(session_id): Path<String>,
+    Json(request): Json<BuildImageRequest>,
+) -> ApiResult<(StatusCode, Json<ImageBuildStarted>)> {
+    let session = state
+        .sessions
+        .get_session_for_user(user.id(), &session_id)
+        .await?
+        .ok_or_else(|| ApiError::not_found(format!("Session {session_id} not found")))?;
+    if !state.sessions.can_build_images() {
+        return Err(ApiError::bad_request(
+            "Image builds need the container runtime",
+        ));
+    }
+
+    let workspace = PathBuf::from(&session.workspace_path);
+    let dockerfile = image_builds::resolve_dockerfile(&workspace, &request.dockerfile)
+        .map_err(ApiError::bad_request)?;
+    let name = request.name.unwrap_or_else(|| {
+        workspace
+            .file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or_default()
+    });
+    let image = image_builds::image_tag(user.id(), &name);
+    let guard = state
+        .image_builds
+        .start(&session.id)
+        .ok_or_else(|| ApiError::conflict("An image build is already running for this session"))?;
+
+    let build_id = format!("build_{}", nanoid::nanoid!(10));
+    let spec = ImageBuildSpec {
+        context: workspace,
+        dockerfile,
+        tag: image.clone(),
+        labels: vec![
+            ("oqto.user".to_string(), user.id().to_string()),
+            ("oqto.session".to_string(), session.id.clone()),
+        ],
+    };
+    info!(
+        session_id = %session.id,
+        build_id = %build_id,
+        image = %image,
+        "Starting image build"
+    );
+
+    let user_id = user.id().to_string();
+    let started = ImageBuildStarted {
+        build_id: build_id.clone(),
+        image: image.clone(),
+    };
+    tokio::spawn(async move {
+        let _guard = guard;
+        let session_id = session.id;
+        send_event(
+            &state,
+            &user_id,
+            &session_id,
+            EventPayload::ImageBuildStarted {
+                build_id: build_id.clone(),
+                image: image.clone(),
+            },
+        )
+        .await;
+
+        let (tx, mut rx) = mpsc::channel(256);
+        let build = tokio::time::timeout(BUILD_TIMEOUT, state.sessions.build_image(&spec, tx));
+        let forward = async {
+            let mut sent = 0;
+            while let Some(line) = rx.recv().await {
+                if sent == MAX_OUTPUT_LINES {
+                    continue;
+                }
+                sent += 1;
+                let payload = EventPayload::ImageBuildOutput {
+                    build_id: build_id.clone(),
+                    line,
+                };
+                send_event(&state, &user_id, &session_id, payload).await;
+            }
+        };
+        let (result, ()) = tokio::join!(build, forward);
+
+        let error = match result {
+            Ok(Ok(())) => None,
+            Ok(Err(e)) => Some(format!("{e:#}")),
+            Err(_) => Some(format!(
+                "build timed out after {} minutes",
+                BUILD_TIMEOUT.as_secs() / 60
+            )),
+        };
+        match &error {
+            None => info!(session_id = %session_id, image = %image, "Image build succeeded"),
+            Some(e) => warn!(session_id = %session_id, image = %image, "Image build failed: {e}"),
+        }
+        send_event(
+            &state,
+            &user_id,
+            &session_id,
+            EventPayload::ImageBuildFinished {
+                build_id,
+                image,
+                success: error.is_none(),
+                error,
+            },
+        )
+        .await;
+    });
+
+    Ok((StatusCode::ACCEPTED, Json(started)))
+}
@@ -0,0 +1,596 @@
+//! Admin impersonation handlers.
+
+use std::sync::Arc;
+
+use axum::{
+    Json,
+    extract::{Path, Query, State},
+    http::StatusCode,
+};
+use tracing::{info, instrument};
+
+use crate::audit::{AuditPage, AuditQuery};
+use crate::auth::{Actor, CurrentUser, RequireAdmin, RevocationScope};
+use crate::db;
+use crate::impersonation::{
+    Impersonation, ImpersonationListQuery, ImpersonationService, ImpersonationStatus,
+    ImpersonationToken, RequestImpersonation,
+};
+use crate::user::UserRole;
+use crate::ws::types::WsEvent;
+
+use crate::api::error::{ApiError, ApiResult};
+use crate::api::state::AppState;
+
+/// Most events returned by the activity endpoint.
+const ACTIVITY_LIMIT: usize = 1000;
+
+fn service(state: &AppState) -> ApiResult<&Arc<ImpersonationService>> {
+    state
+        .impersonations
+        .as_ref()
+        .ok_or_else(|| ApiError::not_found("Impersonation is disabled"))
+}
+
+/// Refuse an impersonated request with "`what` while impersonating". For
+/// actions that would let the admin keep access after the impersonation
+/// expires (credentials, trigger tokens, shares, memberships, making
+/// private messages readable) and for the user's own decisions.
+pub(crate) fn refuse_impersonated(user: &CurrentUser, what: &str) -> ApiResult<()> {
+    if user.impersonator().is_some() {
+        return Err(ApiError::forbidden(format!("{what} while impersonating")));
+    }
+    Ok(())
+}
+
+/// Consent and ending are the user's own decisions.
+fn require_own_session(user: &CurrentUser) -> ApiResult<()> {
+    refuse_impersonated(user, "Not available")
+}
+
+async fn get_impersonation(service: &ImpersonationService, id: &str) -> ApiResult<Impersonation> {
+    service
+        .repo()
+        .get(id)
+        .await?
+        .ok_or_else(|| ApiError::not_found(format!("Impersonation {id} not found")))
+}
+
+/// An impersonation of the caller.
+async fn find_mine(
+    service: &ImpersonationService,
+    user: &CurrentUser,
+    id: &str,
+) -> ApiResult<Impersonation> {
+    let impersonation = get_impersonation(service, id).await?;
+    if impersonation.user_id != user.id() {
+        return Err(ApiError::not_found(format!("Impersonation {id} not found")));
+    }
+    Ok(impersonation)
+}
+
+fn not_open(impersonation: &Impersonation) -> ApiError {
+    ApiError::conflict(format!(
+        "Impersonation {} is {}",
+        impersonation.id,
+        impersonation.status.as_str()
+    ))
+}
+
+async fn audit(state: &AppState, event: &str, impersonation: &Impersonation) {
+    if let Some(logger) = state.audit_logger.as_ref() {
+        logger.log_impersonation(event, impersonation).await;
+    }
+}
+
+async fn notify(
+    state: &AppState,
+    user_id: &str,
+    level: &str,
+    title: &str,
+    message: String,
+    impersonation: &Impersonation,
+) {
+    state
+        .ws_hub
+        .send_to_user(
+            user_id,
+            WsEvent::Notification {
+                level: level.to_string(),
+                title: title.to_string(),
+                message,
+                category: "impersonation".to_string(),
+                detail: serde_json::to_value(impersonation).ok(),
+            },
+        )
+        .await;
+}
+
+/// Close the admin's impersonated sockets, record the end and tell the
+/// other side, with how many actions were recorded.
+async fn finish(state: &AppState, impersonation: &Impersonation, ended_by: &CurrentUser) {
+    state.auth.revoke_user_tokens(
+        &impersonation.user_id,
+        RevocationScope::Impersonation,
+        "impersonation ended",
+    );
+    audit(state, "impersonation_ended", impersonation).await;
+
+    let actions = match state.audit_logger.as_ref() {
+        Some(logger) => logger
+            .query(&AuditQuery {
+                impersonation_id: Some(impersonation.id.clone()),
+                event: Some("http_request".to_string()),
+                ..Default::default()
+            })
+            .await
+            .map(|page| page.total)
+            .ok(),
+        None => None,
+    };
+    let recorded = actions
+        .map(|n| format!(" {n} requests were recorded."))
+        .unwrap_or_default();
+    let (recipient, message) = if ended_by.id() == impersonation.user_id {
+        (
+            &impersonation.admin_id,
+            format!(
+                "{} ended your impersonation.{recorded}",
+                ended_by.display_name()
+            ),
+        )
+    } else {
+        (
+            &impersonation.user_id,
+            format!(
+                "{} stopped acting as you.{recorded} See what was done under Impersonations.",
+                ended_by.display_name()
+            ),
+        )
+    };
+    notify(
+        state,
+        recipient,
+        "info",
+        "Impersonation ended",
+        message,
+        impersonation,
+    )
+    .await;
+}
+
+/// Ask to act as a user, or start right away with a break-glass
+/// justification when policy allows (admin only).
+///
+/// POST /api/admin/impersonations
+#[instrument(skip(state, admin, request))]
+pub async fn request_impersonation(
+    State(state): State<AppState>,
+    RequireAdmin(admin): RequireAdmin,
+    Json(request): Json<RequestImpersonation>,
+) -> ApiResult<(StatusCode, Json<Impersonation>)> {
+    let service = service(&state)?;
+    if request.user_id == admin.id() {
+        return Err(ApiError::bad_request("You cannot impersonate yourself"));
+    }
+    let target = state
+        .users
+        .get_user(&request.user_id)
+        .await?
+        .ok_or_else(|| ApiError::not_found(format!("User {} not found", request.user_id)))?;
+    if target.role != UserRole::User {
+        return Err(ApiError::forbidden(
+            "Only regular users can be impersonated",
+        ));
+    }
+    if !target.is_active {
+        return Err(ApiError::bad_request(format!(
+            "User {} is deactivated",
+            target.id
+        )));
+    }
+    let duration = service
+        .check_request(&request)
+        .map_err(ApiError::bad_request)?;
+    if let Some(open) = service.repo().open_for_user(&target.id).await? {
+        return Err(ApiError::conflict(format!(
+            "User {} already has a {} impersonation ({})",
+            target.id,
+            open.status.as_str(),
+            open.id
+        )));
+    }
+
+    let impersonation = service.request(admin.id(), &request, duration).await?;
+    if impersonation.break_glass {
+        audit(&state, "impersonation_break_glass", &impersonation).await;
+        notify(
+            &state,
+            &target.id,
+            "warning",
+            "An administrator is acting as you",
+            format!(
+                "{} started acting as you for up to {} minutes without asking first: {}. \
+                 You can end it at any time and see what was done.",
+                admin.display_name(),
+                impersonation.duration_minutes,
+                impersonation.reason
+            ),
+            &impersonation,
+        )
+        .await;
+    } else {
+        audit(&state, "impersonation_requested", &impersonation).await;
+        notify(
+            &state,
+            &target.id,
+            "warning",
+            "An administrator asks to act as you",
+            format!(
+                "{} asks to act as you for {} minutes: {}. Approve or deny within {} minutes.",
+                admin.display_name(),
+                impersonation.duration_minutes,
+                impersonation.reason,
+                service.config().consent_timeout_minutes
+            ),
+            &impersonation,
+        )
+        .await;
+    }
+    Ok((StatusCode::CREATED, Json(impersonation)))
+}
+
+/// List impersonations, newest first (admin only).
+///
+/// GET /api/admin/impersonations
+#[instrument(skip(state, _admin))]
+pub async fn admin_list_impersonations(
+    State(state): State<AppState>,
+    RequireAdmin(_admin): RequireAdmin,
+    Query(query): Query<ImpersonationListQuery>,
+) -> ApiResult<Json<Vec<Impersonation>>> {
+    Ok(Json(service(&state)?.repo().list(&query).await?))
+}
+
+/// Issue a token to act as the user with, valid until the impersonation
+/// ends. Only the admin who asked gets one.
+///
+/// POST /api/admin/impersonations/{id}/token
+#[instrument(skip(state, admin))]
+pub async fn admin_issue_impersonation_token(
+    State(state): State<AppState>,
+    RequireAdmin(admin): RequireAdmin,
+    Path(id): Path<String>,
+) -> ApiResult<Json<ImpersonationToken>> {
+    let service = service(&state)?;
+    let impersonation = get_impersonation(service, &id).await?;
+    if impersonation.admin_id != admin.id() {
+        return Err(ApiError::forbidden(
+            "Only the admin who requested the impersonation can use it",
+        ));
+    }
+    if impersonation.status != ImpersonationStatus::Active {
+        return Err(not_open(&impersonation));
+    }
+    let target = state
+        .users
+        .get_user(&impersonation.user_id)
+        .await?
+        .ok_or_else(|| ApiError::not_found(format!("User {} not found", impersonation.user_id)))?;
+    let expires_at = db::parse_timestamp(&impersonation.expires_at)
+        .ok_or_else(|| ApiError::internal("Invalid impersonation expiry"))?
+        .timestamp();
+
+    let token = state.auth.generate_impersonation_token(
+        &target.id,
+        &target.email,
+        &target.display_name,
+        &target.role.to_string(),
+        Actor {
+            sub: admin.id().to_string(),
+            impersonation_id: impersonation.id.clone(),
+        },
+        expires_at,
+    )?;
+    audit(&state, "impersonation_token_issued", &impersonation).await;
+    info!(
+        impersonation_id = %impersonation.id,
+        admin_id = %admin.id(),
+        user_id = %impersonation.user_id,
+        "Issued impersonation token"
+    );
+    Ok(Json(ImpersonationToken {
+        token,
+        expires_at,
+        impersonation,
+    }))
+}
+
+/// End an impersonation early, or withdraw a pending request (admin only).
+///
+/// POST /api/admin/impersonations/{id}/end
+#[instrument(skip(state, admin))]
+pub async fn admin_end_impersonation(
+    State(state): State<AppState>,
+    RequireAdmin(admin): RequireAdmin,
+    Path(id): Path<String>,
+) -> ApiResult<Json<Impersonation>> {
+    let service = service(&state)?;
+    let impersonation = get_impersonation(service, &id).await?;
+    let ended = service
+        .end(&impersonation, admin.id())
+        .await?
+        .ok_or_else(|| not_open(&impersonation))?;
+    finish(&state, &ended, &admin).await;
+    Ok(Json(ended))
+}
+
+/// The caller's impersonations, newest first: requests waiting for an
+/// answer, the active one and past ones.
+///
+/// GET /api/me/impersonations
+#[instrument(skip(state, user))]
+pub async fn list_my_impersonations(
+    State(state): State<AppState>,
+    user: CurrentUser,
+) -> ApiResult<Json<Vec<Impersonation>>> {
+    let query = ImpersonationListQuery {
+        user_id: Some(user.id().to_string()),
+        ..Default::default()
+    };
+    Ok(Json(service(&state)?.repo().list(&query).await?))
+}
+
+/// Let the admin act as the caller for the requested duration.
+///
+/// POST /api/me/impersonations/{id}/approve
+#[instrument(skip(state, user))]
+pub async fn approve_impersonation(
+    State(state): State<AppState>,
+    user: CurrentUser,
+    Path(id): Path<String>,
+) -> ApiResult<Json<Impersonation>> {
+    require_own_session(&user)?;
+    let service = service(&state)?;
+    let impersonation = find_mine(service, &user, &id).await?;
+    let approved = service
+        .approve(&impersonation)
+        .await?
+        .ok_or_else(|| not_open(&impersonation))?;
+    audit(&state, "impersonation_approved", &approved).await;
+    notify(
+        &state,
+        &approved.admin_id,
+        "info",
+        "Impersonation approved",
+        format!(
+            "{} approved your request for {} minutes",
+            user.display_name(),
+            approved.duration_minutes
+        ),
+        &approved,
+    )
+    .await;
+    Ok(Json(approved))
+}
+
+/// Refuse an impersonation request.
+///
+/// POST /api/me/impersonations/{id}/deny
+#[instrument(skip(state, user))]
+pub async fn deny_impersonation(
+    State(state): State<AppState>,
+    user: CurrentUser,
+    Path(id): Path<String>,
+) -> ApiResult<Json<Impersonation>> {
+    require_own_session(&user)?;
+    let service = service(&state)?;
+    let impersonation = find_mine(service, &user, &id).await?;
+    let denied = service
+        .deny(&impersonation)
+        .await?
+        .ok_or_else(|| not_open(&impersonation))?;
+    audit(&state, "impersonation_denied", &denied).await;
+    notify(
+        &state,
+        &denied.admin_id,
+        "info",
+        "Impersonation denied",
+        format!("{} denied your request", user.display_name()),
+        &denied,
+    )
+    .await;
+    Ok(Json(denied))
+}
+
+/// End an impersonation of the caller early.
+///
+/// POST /api/me/impersonations/{id}/end
+#[instrument(skip(state, user))]
+pub async fn end_my_impersonation(
+    State(state): State<AppState>,
+    user: CurrentUser,
+    Path(id): Path<String>,
+) -> ApiResult<Json<Impersonation>> {
+    require_own_session(&user)?;
+    let service = service(&state)?;
+    let impersonation = find_mine(service, &user, &id).await?;
+    let ended = service
+        .end(&impersonation, user.id())
+        .await?
+        .ok_or_else(|| not_open(&impersonation))?;
+    finish(&state, &ended, &user).await;
+    Ok(Json(ended))
+}
+
+/// Everything recorded during an impersonation of the caller, newest
+/// first: its own steps and what the admin did.
+///
+/// GET /api/me/impersonations/{id}/activity
+#[instrument(skip(state, user))]
+pub async fn get_impersonation_activity(
+    State(state): State<AppState>,
+    user: CurrentUser,
+    Path(id): Path<String>,
+) -> ApiResult<Json<AuditPage>> {
+    let service = service(&state)?;
+    let impersonation = find_mine(service, &user, &id).await?;
+    let logger = state
+        .audit_logger
+        .as_ref()
+        .ok_or_else(|| ApiError::not_found("Audit logging is disabled"))?;
+    let page = logger
+        .query(&AuditQuery {
+            impersonation_id: Some(impersonation.id),
+            limit: ACTIVITY_LIMIT,
+            ..Default::default()
+        })
+        .await?;
+    Ok(Json(page))
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::response::IntoResponse;
+    use serde::de::DeserializeOwned;
+    use serde_json::json;
+
+    use super::super::{api_keys, private_messages, session_shares, shared_workspaces, triggers};
+    use super::*;
+    use crate::auth::Claims;
+
+    fn impersonated() -> CurrentUser {
+        CurrentUser {
+            claims: Claims {
+                sub: "alice".to_string(),
+                iss: None,
+                aud: None,
+                exp: chrono::Utc::now().timestamp() + 3600,
+                iat: None,
+                nbf: None,
+                jti: None,
+                email: None,
+                name: None,
+                preferred_username: None,
+                roles: vec!["user".to_string()],
+                role: None,
+                act: Some(Actor {
+                    sub: "admin".to_string(),
+                    impersonation_id: "imp_1".to_string(),
+                }),
+                scope: None,
+            },
+        }
+    }
+
+    fn body<T: DeserializeOwned>(value: serde_json::Value) -> Json<T> {
+        Json(serde_json::from_value(value).unwrap())
+    }
+
+    fn status<T>(result: ApiResult<T>) -> StatusCode {
+        match result {
+            Ok(_) => StatusCode::OK,
+            Err(e) => e.into_response().status(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_impersonation_cannot_grant_lasting_access() {
+        let state = AppState::for_tests().await;
+        let s = || State(state.clone());
+        let path = |id: &str| Path(id.to_string());
+        let members = |path: &str| Path(("ws_1".to_string(), path.to_string()));
+
+        let statuses = [
+            status(
+                api_keys::create_api_key(s(), impersonated(), body(json!({ "name": "ci" }))).await,
+            ),
+            status(
+                triggers::create_trigger(
+                    s(),
+                    impersonated(),
+                    body(json!({ "name": "deploy", "workspace_path": "/w", "prompt": "go" })),
+                )
+                .await,
+            ),
+            status(triggers::rotate_trigger_token(s(), impersonated(), path("deploy")).await),
+            status(
+                session_shares::share_session(
+                    s(),
+                    impersonated(),
+                    path("ses_1"),
+                    body(json!({ "user_id": "admin" })),
+                )
+                .await,
+            ),
+            status(
+                shared_workspaces::create_shared_workspace(
+                    s(),
+                    impersonated(),
+                    body(json!({ "name": "team", "member_ids": ["admin"] })),
+                )
+                .await,
+            ),
+            status(
+                shared_workspaces::convert_to_shared_workspace(
+                    s(),
+                    impersonated(),
+                    body(json!({ "source_path": "/w", "name": "team", "member_ids": ["admin"] })),
+                )
+                .await,
+            ),
+            status(
+                shared_workspaces::add_shared_workspace_member(
+                    s(),
+                    impersonated(),
+                    path("ws_1"),
+                    body(json!({ "user_id": "admin" })),
+                )
+                .await,
+            ),
+            status(
+                shared_workspaces::update_shared_workspace_member(
+                    s(),
+                    impersonated(),
+                    members("admin"),
+                    body(json!({ "role": "admin" })),
+                )
+                .await,
+            ),
+            status(
+                shared_workspaces::update_shared_workspace_member_permissions(
+                    s(),
+                    impersonated(),
+                    members("admin"),
+                    body(json!({})),
+                )
+                .await,
+            ),
+            status(
+                shared_workspaces::transfer_shared_workspace_ownership(
+                    s(),
+                    impersonated(),
+                    path("ws_1"),
+                    body(json!({ "new_owner_id": "admin" })),
+                )
+                .await,
+            ),
+            status(
+                private_messages::mark_message_private(
+                    s(),
+                    impersonated(),
+                    path("ses_1"),
+                    Query(serde_json::from_value(json!({})).unwrap()),
+                    body(json!({ "message_id": "m1" })),
+                )
+                .await,
+            ),
+            status(
+                private_messages::delete_private_message(s(), impersonated(), path("pm_1")).await,
+            ),
+        ];
+        for (i, status) in statuses.iter().enumerate() {
+            assert_eq!(*status, StatusCode::FORBIDDEN, "handler {i}");
+        }
+    }
+}
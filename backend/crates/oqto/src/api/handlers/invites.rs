@@ -1,54 +1,59 @@
-//! Invite code management handlers (admin only).
+//! Invite code management handlers (admin or `invites.manage`).
 
 use axum::{
     Json,
     extract::{Path, Query, State},
-    http::StatusCode,
+    http::{StatusCode, header},
+    response::{IntoResponse, Response},
 };
 use serde::Serialize;
 use tracing::{info, instrument};
 
-use crate::auth::RequireAdmin;
+use crate::auth::{Access, Permission};
 use crate::invite::{
-    BatchCreateInviteCodesRequest, CreateInviteCodeRequest, InviteCodeListQuery, InviteCodeSummary,
+    BatchCreateInviteCodesRequest, BatchRevokeInviteCodesRequest, CreateInviteCodeRequest,
+    ImportInviteCodesRequest, ImportInviteCodesResult, InviteCodeExportQuery, InviteCodeListQuery,
+    InviteCodeRedemption, InviteCodeSummary,
 };
 
 use crate::api::error::{ApiError, ApiResult};
 use crate::api::state::AppState;
 
-/// List all invite codes (admin only).
-#[instrument(skip(state, user))]
+/// List all invite codes (admin or `invites.manage`).
+#[instrument(skip(state, access))]
 pub async fn list_invite_codes(
     State(state): State<AppState>,
-    RequireAdmin(user): RequireAdmin,
+    access: Access,
     Query(query): Query<InviteCodeListQuery>,
 ) -> ApiResult<Json<Vec<InviteCodeSummary>>> {
-    let _ = user;
+    access.require(Permission::InvitesManage)?;
     let codes = state.invites.list(query).await?;
     let summaries: Vec<InviteCodeSummary> = codes.into_iter().map(|c| c.into()).collect();
     info!(count = summaries.len(), "Listed invite codes");
     Ok(Json(summaries))
 }
 
-/// Create a single invite code (admin only).
-#[instrument(skip(state, user, request))]
+/// Create a single invite code (admin or `invites.manage`).
+#[instrument(skip(state, access, request))]
 pub async fn create_invite_code(
     State(state): State<AppState>,
-    RequireAdmin(user): RequireAdmin,
+    access: Access,
     Json(request): Json<CreateInviteCodeRequest>,
 ) -> ApiResult<(StatusCode, Json<InviteCodeSummary>)> {
+    let user = access.require(Permission::InvitesManage)?;
     let code = state.invites.create(request, user.id()).await?;
     info!(code_id = %code.id, "Created invite code");
     Ok((StatusCode::CREATED, Json(code.into())))
 }
 
-/// Create multiple invite codes at once (admin only).
-#[instrument(skip(state, user, request))]
+/// Create multiple invite codes at once (admin or `invites.manage`).
+#[instrument(skip(state, access, request))]
 pub async fn create_invite_codes_batch(
     State(state): State<AppState>,
-    RequireAdmin(user): RequireAdmin,
+    access: Access,
     Json(request): Json<BatchCreateInviteCodesRequest>,
 ) -> ApiResult<(StatusCode, Json<Vec<InviteCodeSummary>>)> {
+    let user = access.require(Permission::InvitesManage)?;
     let codes = state
         .invites
         .create_batch(
@@ -66,14 +71,113 @@ pub async fn create_invite_codes_batch(
     Ok((StatusCode::CREATED, Json(summaries)))
 }
 
-/// Get a specific invite code (admin only).
-#[instrument(skip(state, user))]
+/// Import pre-generated invite codes from JSON and/or CSV (admin or `invites.manage`).
+///
+/// Codes that already exist or repeat within the import are skipped and
+/// listed under `duplicates`.
+#[instrument(skip(state, access, request))]
+pub async fn import_invite_codes(
+    State(state): State<AppState>,
+    access: Access,
+    Json(request): Json<ImportInviteCodesRequest>,
+) -> ApiResult<(StatusCode, Json<ImportInviteCodesResult>)> {
+    let user = access.require(Permission::InvitesManage)?;
+    let result = state
+        .invites
+        .import(request, user.id())
+        .await
+        .map_err(|e| ApiError::bad_request(format!("{e:#}")))?;
+    info!(
+        imported = result.imported.len(),
+        duplicates = result.duplicates.len(),
+        "Imported invite codes"
+    );
+    Ok((StatusCode::CREATED, Json(result)))
+}
+
+/// Export invite codes as CSV (default) or JSON (admin or `invites.manage`).
+#[instrument(skip(state, access))]
+pub async fn export_invite_codes(
+    State(state): State<AppState>,
+    access: Access,
+    Query(query): Query<InviteCodeExportQuery>,
+) -> ApiResult<Response> {
+    access.require(Permission::InvitesManage)?;
+    let codes = state.invites.list_for_export(&query).await?;
+    info!(count = codes.len(), "Exported invite codes");
+    match query.format.as_deref().unwrap_or("csv") {
+        "csv" => Ok((
+            [
+                (header::CONTENT_TYPE, "text/csv; charset=utf-8"),
+                (
+                    header::CONTENT_DISPOSITION,
+                    "attachment; filename=\"invite-codes.csv\"",
+                ),
+            ],
+            crate::invite::csv::export(&codes),
+        )
+            .into_response()),
+        "json" => {
+            let summaries: Vec<InviteCodeSummary> = codes.into_iter().map(Into::into).collect();
+            Ok(Json(summaries).into_response())
+        }
+        other => Err(ApiError::bad_request(format!(
+            "Unsupported export format '{other}' (use csv or json)"
+        ))),
+    }
+}
+
+/// Response of a batch revocation.
+#[derive(Debug, Serialize)]
+pub struct BatchRevokeInviteCodesResponse {
+    pub revoked: u64,
+}
+
+/// Revoke every usable invite code with a given note and/or code prefix
+/// (admin or `invites.manage`).
+#[instrument(skip(state, access))]
+pub async fn revoke_invite_codes_batch(
+    State(state): State<AppState>,
+    access: Access,
+    Json(request): Json<BatchRevokeInviteCodesRequest>,
+) -> ApiResult<Json<BatchRevokeInviteCodesResponse>> {
+    access.require(Permission::InvitesManage)?;
+    if request.note.is_none() && request.prefix.as_deref().is_none_or(str::is_empty) {
+        return Err(ApiError::bad_request("note or prefix is required"));
+    }
+    let revoked = state
+        .invites
+        .revoke_matching(request.note.as_deref(), request.prefix.as_deref())
+        .await?;
+    info!(revoked, "Revoked invite codes in batch");
+    Ok(Json(BatchRevokeInviteCodesResponse { revoked }))
+}
+
+/// Usage history of an invite code: who redeemed it and when (admin or `invites.manage`).
+#[instrument(skip(state, access))]
+pub async fn list_invite_code_redemptions(
+    State(state): State<AppState>,
+    access: Access,
+    Path(code_id): Path<String>,
+) -> ApiResult<Json<Vec<InviteCodeRedemption>>> {
+    access.require(Permission::InvitesManage)?;
+    if state.invites.get(&code_id).await?.is_none() {
+        return Err(ApiError::not_found(format!(
+            "Invite code {} not found",
+            code_id
+        )));
+    }
+    Ok(Json(state.invites.list_redemptions(&code_id).await?))
+}
+
+/// Get a specific invite code (admin or `invites.manage`).
+#[instrument(skip(state, access))]
 pub async fn get_invite_code(
     State(state): State<AppState>,
-    RequireAdmin(user): RequireAdmin,
+    access: Access,
     Path(code_id): Path<String>,
 ) -> ApiResult<Json<InviteCodeSummary>> {
-    let _ = user;
+    access.require(Permission::InvitesManage)?;
     state
         .invites
         .get(&code_id)
@@ -82,45 +186,45 @@ pub async fn get_invite_code(
         .ok_or_else(|| ApiError::not_found(format!("Invite code {} not found", code_id)))
 }
 
-/// Revoke an invite code (admin only).
-#[instrument(skip(state, user))]
+/// Revoke an invite code (admin or `invites.manage`).
+#[instrument(skip(state, access))]
 pub async fn revoke_invite_code(
     State(state): State<AppState>,
-    RequireAdmin(user): RequireAdmin,
+    access: Access,
     Path(code_id): Path<String>,
 ) -> ApiResult<StatusCode> {
-    let _ = user;
+    access.require(Permission::InvitesManage)?;
     state.invites.revoke(&code_id).await?;
     info!(code_id = %code_id, "Revoked invite code");
     Ok(StatusCode::NO_CONTENT)
 }
 
-/// Delete an invite code (admin only).
-#[instrument(skip(state, user))]
+/// Delete an invite code (admin or `invites.manage`).
+#[instrument(skip(state, access))]
 pub async fn delete_invite_code(
     State(state): State<AppState>,
-    RequireAdmin(user): RequireAdmin,
+    access: Access,
     Path(code_id): Path<String>,
 ) -> ApiResult<StatusCode> {
-    let _ = user;
+    access.require(Permission::InvitesManage)?;
     state.invites.delete(&code_id).await?;
     info!(code_id = %code_id, "Deleted invite code");
     Ok(StatusCode::NO_CONTENT)
 }
 
-/// Get invite code statistics (admin only).
+/// Get invite code statistics (admin or `invites.manage`).
 #[derive(Debug, Serialize)]
 pub struct InviteCodeStats {
     pub total: i64,
     pub valid: i64,
 }
 
-#[instrument(skip(state, user))]
+#[instrument(skip(state, access))]
 pub async fn get_invite_code_stats(
     State(state): State<AppState>,
-    RequireAdmin(user): RequireAdmin,
+    access: Access,
 ) -> ApiResult<Json<InviteCodeStats>> {
-    let _ = user;
+    access.require(Permission::InvitesManage)?;
     let total = state.invites.count().await?;
     let valid = state.invites.count_valid().await?;
     Ok(Json(InviteCodeStats { total, valid }))
@@ -0,0 +1,188 @@
+//! User macro handlers: manage macros and run them against sessions.
+
+use axum::{
+    Json,
+    extract::{Path, State},
+    http::StatusCode,
+};
+use tracing::instrument;
+
+use crate::auth::CurrentUser;
+use crate::macros::{
+    MacroRun, MacroService, RunMacroRequest, RunnerAgent, SaveMacroRequest, StepStatus, UserMacro,
+};
+use crate::session_target::SessionTargetScope;
+use crate::shared_workspace::SharePermission;
+
+use super::chat::session_runner;
+use crate::api::error::{ApiError, ApiResult};
+use crate::api::state::AppState;
+
+fn macros(state: &AppState) -> ApiResult<&MacroService> {
+    state
+        .macros
+        .as_deref()
+        .ok_or_else(|| ApiError::not_found("Macros are not enabled"))
+}
+
+/// Validate a definition and trim its name.
+fn checked(service: &MacroService, mut request: SaveMacroRequest) -> ApiResult<SaveMacroRequest> {
+    request.name = request.name.trim().to_string();
+    crate::macros::validate(&request, service.config()).map_err(ApiError::bad_request)?;
+    Ok(request)
+}
+
+/// Load one of the caller's macros. Other users' macros are reported as
+/// missing.
+async fn owned_macro(
+    service: &MacroService,
+    macro_id: &str,
+    user_id: &str,
+) -> ApiResult<UserMacro> {
+    service
+        .repo()
+        .get(macro_id)
+        .await?
+        .filter(|m| m.user_id == user_id)
+        .ok_or_else(|| ApiError::not_found(format!("Macro {macro_id} not found")))
+}
+
+async fn require_unique_name(
+    service: &MacroService,
+    user_id: &str,
+    name: &str,
+    except_id: Option<&str>,
+) -> ApiResult<()> {
+    if service.repo().name_taken(user_id, name, except_id).await? {
+        return Err(ApiError::bad_request(format!(
+            "A macro named '{name}' already exists"
+        )));
+    }
+    Ok(())
+}
+
+/// The caller's macros.
+#[instrument(skip(state, user))]
+pub async fn list_macros(
+    State(state): State<AppState>,
+    user: CurrentUser,
+) -> ApiResult<Json<Vec<UserMacro>>> {
+    Ok(Json(macros(&state)?.repo().list_for_user(user.id()).await?))
+}
+
+/// Define a macro.
+#[instrument(skip(state, user, request))]
+pub async fn create_macro(
+    State(state): State<AppState>,
+    user: CurrentUser,
+    Json(request): Json<SaveMacroRequest>,
+) -> ApiResult<(StatusCode, Json<UserMacro>)> {
+    let service = macros(&state)?;
+    let request = checked(service, request)?;
+    if service.repo().count_for_user(user.id()).await? >= service.config().max_macros_per_user {
+        return Err(ApiError::too_many_requests("Too many macros"));
+    }
+    require_unique_name(service, user.id(), &request.name, None).await?;
+
+    let created = service.repo().create(user.id(), &request).await?;
+    Ok((StatusCode::CREATED, Json(created)))
+}
+
+#[instrument(skip(state, user))]
+pub async fn get_macro(
+    State(state): State<AppState>,
+    user: CurrentUser,
+    Path(macro_id): Path<String>,
+) -> ApiResult<Json<UserMacro>> {
+    Ok(Json(
+        owned_macro(macros(&state)?, &macro_id, user.id()).await?,
+    ))
+}
+
+/// Replace a macro's definition.
+#[instrument(skip(state, user, request))]
+pub async fn update_macro(
+    State(state): State<AppState>,
+    user: CurrentUser,
+    Path(macro_id): Path<String>,
+    Json(request): Json<SaveMacroRequest>,
+) -> ApiResult<Json<UserMacro>> {
+    let service = macros(&state)?;
+    let existing = owned_macro(service, &macro_id, user.id()).await?;
+    let request = checked(service, request)?;
+    require_unique_name(service, user.id(), &request.name, Some(&existing.id)).await?;
+
+    service.repo().update(&existing.id, &request).await?;
+    Ok(Json(owned_macro(service, &existing.id, user.id()).await?))
+}
+
+#[instrument(skip(state, user))]
+pub async fn delete_macro(
+    State(state): State<AppState>,
+    user: CurrentUser,
+    Path(macro_id): Path<String>,
+) -> ApiResult<StatusCode> {
+    let service = macros(&state)?;
+    let existing = owned_macro(service, &macro_id, user.id()).await?;
+    service.repo().delete(&existing.id).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Run a macro against a session the caller can write to. Responds once
+/// the run finished, with the result of every step.
+#[instrument(skip(state, user, request), fields(session_id = %request.session_id))]
+pub async fn run_macro(
+    State(state): State<AppState>,
+    user: CurrentUser,
+    Path(macro_id): Path<String>,
+    Json(request): Json<RunMacroRequest>,
+) -> ApiResult<Json<MacroRun>> {
+    let service = macros(&state)?;
+    let user_macro = owned_macro(service, &macro_id, user.id()).await?;
+    let steps = crate::macros::resolve_steps(&user_macro, &request.params)
+        .map_err(ApiError::bad_request)?;
+
+    let workspace_id = match request.shared_workspace_id.clone() {
+        Some(id) => Some(id),
+        None => state
+            .session_targets
+            .get(&request.session_id)
+            .await?
+            .filter(|record| record.scope == SessionTargetScope::SharedWorkspace)
+            .and_then(|record| record.workspace_id),
+    };
+    if let Some(workspace_id) = &workspace_id {
+        let shared = state
+            .shared_workspaces
+            .as_ref()
+            .ok_or_else(|| ApiError::not_found("Shared workspaces are not enabled"))?;
+        if !shared
+            .permissions_for_workspace(workspace_id, user.id())
+            .await?
+            .allows(SharePermission::ChatWrite)
+        {
+            return Err(ApiError::forbidden(
+                "Missing 'chat_write' permission in this shared workspace",
+            ));
+        }
+    }
+
+    let runner = session_runner(
+        &state,
+        user.id(),
+        &request.session_id,
+        workspace_id.as_deref(),
+    )
+    .await?;
+    let mut agent = RunnerAgent::connect(runner, &request.session_id)
+        .await
+        .map_err(|e| ApiError::service_unavailable(format!("session subscribe failed: {e:#}")))?;
+
+    let steps = crate::macros::run_steps(&mut agent, &steps, service.step_timeout()).await;
+    Ok(Json(MacroRun {
+        macro_id: user_macro.id,
+        session_id: request.session_id,
+        success: steps.iter().all(|s| s.status == StepStatus::Ok),
+        steps,
+    }))
+}
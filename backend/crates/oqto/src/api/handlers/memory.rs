@@ -0,0 +1,280 @@
+//! Memory promotion handlers: session findings into mmry, and the review
+//! queue for agent suggestions.
+
+use axum::{
+    Json,
+    extract::{Path, Query, State},
+    http::StatusCode,
+};
+use serde::Serialize;
+use tracing::instrument;
+
+use crate::auth::CurrentUser;
+use crate::memory_promotion::{
+    AcceptSuggestionRequest, MemoryPromotionService, MemorySuggestion, NewSuggestion,
+    PromoteMemoryRequest, PromotedMemory, SuggestMemoryRequest, SuggestionListQuery,
+    SuggestionStatus,
+};
+
+use super::chat::{SessionArtifactQuery, session_runner};
+use crate::api::error::{ApiError, ApiResult};
+use crate::api::state::AppState;
+
+/// Most items promoted by one request.
+const MAX_ITEMS_PER_REQUEST: usize = 50;
+/// Longest accepted suggestion reason.
+const MAX_REASON_LEN: usize = 500;
+
+fn promotion_service(state: &AppState) -> ApiResult<&MemoryPromotionService> {
+    state
+        .memory_promotion
+        .as_deref()
+        .ok_or_else(|| ApiError::not_found("Memory promotion is disabled"))
+}
+
+/// Load a suggestion of the caller. Other users' suggestions are reported as
+/// missing.
+async fn own_suggestion(
+    service: &MemoryPromotionService,
+    suggestion_id: &str,
+    user_id: &str,
+) -> ApiResult<MemorySuggestion> {
+    service
+        .repository()
+        .get(suggestion_id)
+        .await?
+        .filter(|s| s.user_id == user_id)
+        .ok_or_else(|| ApiError::not_found(format!("Suggestion {suggestion_id} not found")))
+}
+
+/// Text of a message's text parts, in order.
+fn message_text(message: &oqto_protocol::projection::ProjectedChatMessage) -> String {
+    message
+        .parts
+        .iter()
+        .filter(|p| p.part_type == "text")
+        .filter_map(|p| p.text.as_deref())
+        .collect::<Vec<_>>()
+        .join("\n\n")
+}
+
+/// Response of a promotion.
+#[derive(Debug, Serialize)]
+pub struct PromoteMemoryResponse {
+    pub memories: Vec<PromotedMemory>,
+}
+
+/// Promote selected messages or findings of a session into the user's
+/// mmry store.
+///
+/// Items referencing a `message_id` without `content` take the message's
+/// text. Each memory records the session, message, promoting user and time
+/// under `metadata.provenance`.
+#[instrument(skip(state, user, request))]
+pub async fn promote_session_memory(
+    State(state): State<AppState>,
+    user: CurrentUser,
+    Path(session_id): Path<String>,
+    Query(query): Query<SessionArtifactQuery>,
+    Json(request): Json<PromoteMemoryRequest>,
+) -> ApiResult<(StatusCode, Json<PromoteMemoryResponse>)> {
+    let service = promotion_service(&state)?;
+    if request.items.is_empty() {
+        return Err(ApiError::bad_request("items must not be empty"));
+    }
+    if request.items.len() > MAX_ITEMS_PER_REQUEST {
+        return Err(ApiError::bad_request(format!(
+            "at most {MAX_ITEMS_PER_REQUEST} items per request"
+        )));
+    }
+
+    let runner = session_runner(
+        &state,
+        user.id(),
+        &session_id,
+        query.shared_workspace_id.as_deref(),
+    )
+    .await?;
+    let session = runner
+        .get_workspace_chat_session(&session_id)
+        .await
+        .map_err(|e| ApiError::internal(format!("runner get session failed: {e:#}")))?
+        .session
+        .ok_or_else(|| ApiError::not_found(format!("Session {session_id} not found")))?;
+
+    let mut messages = if request
+        .items
+        .iter()
+        .any(|item| item.content.is_none() && item.message_id.is_some())
+    {
+        runner
+            .get_workspace_chat_session_messages(
+                &session_id,
+                false,
+                None,
+                oqto_runner::protocol::WorkspaceChatMessagesSource::Authoritative,
+            )
+            .await
+            .map_err(|e| ApiError::internal(format!("runner get messages failed: {e:#}")))?
+            .messages
+    } else {
+        Vec::new()
+    };
+    let hidden = super::private_messages::hidden_from(&state, &session_id, user.id()).await?;
+    crate::private_messages::elide(&mut messages, &hidden);
+
+    let mut items = Vec::with_capacity(request.items.len());
+    for (index, mut item) in request.items.into_iter().enumerate() {
+        if item.content.is_none()
+            && let Some(message_id) = &item.message_id
+        {
+            let message = messages
+                .iter()
+                .find(|m| &m.id == message_id)
+                .ok_or_else(|| {
+                    ApiError::bad_request(format!("item {index}: message {message_id} not found"))
+                })?;
+            item.content = Some(message_text(message));
+        }
+        let item = service
+            .prepare_item(item, &request.tags)
+            .map_err(|e| ApiError::bad_request(format!("item {index}: {e:#}")))?;
+        items.push(item);
+    }
+
+    let memories = service.promote(user.id(), &session_id, &session.workspace_path, &items)?;
+    Ok((
+        StatusCode::CREATED,
+        Json(PromoteMemoryResponse { memories }),
+    ))
+}
+
+/// Queue a memory the agent thinks is worth keeping.
+///
+/// Agents call this through `oqtoctl memory suggest`. Nothing is written to
+/// mmry until the user accepts the suggestion.
+#[instrument(skip(state, user, request))]
+pub async fn suggest_session_memory(
+    State(state): State<AppState>,
+    user: CurrentUser,
+    Path(session_id): Path<String>,
+    Query(query): Query<SessionArtifactQuery>,
+    Json(request): Json<SuggestMemoryRequest>,
+) -> ApiResult<(StatusCode, Json<MemorySuggestion>)> {
+    let service = promotion_service(&state)?;
+    let item = service
+        .prepare_item(request.item, &[])
+        .map_err(|e| ApiError::bad_request(format!("{e:#}")))?;
+    let reason = request
+        .reason
+        .as_deref()
+        .map(str::trim)
+        .filter(|r| !r.is_empty());
+    if reason.is_some_and(|r| r.len() > MAX_REASON_LEN) {
+        return Err(ApiError::bad_request(format!(
+            "reason must be at most {MAX_REASON_LEN} characters"
+        )));
+    }
+
+    let runner = session_runner(
+        &state,
+        user.id(),
+        &session_id,
+        query.shared_workspace_id.as_deref(),
+    )
+    .await?;
+    let session = runner
+        .get_workspace_chat_session(&session_id)
+        .await
+        .map_err(|e| ApiError::internal(format!("runner get session failed: {e:#}")))?
+        .session
+        .ok_or_else(|| ApiError::not_found(format!("Session {session_id} not found")))?;
+
+    if service.repository().count_pending(&session_id).await?
+        >= service.config().max_pending_per_session
+    {
+        return Err(ApiError::too_many_requests(
+            "Too many pending memory suggestions for this session",
+        ));
+    }
+
+    let suggestion = service
+        .suggest(&NewSuggestion {
+            user_id: user.id(),
+            session_id: &session_id,
+            workspace_path: &session.workspace_path,
+            message_id: item.message_id.as_deref(),
+            content: item.content.as_deref().unwrap_or_default(),
+            tags: &item.tags,
+            category: item.category.as_deref(),
+            importance: item.importance,
+            memory_type: item.memory_type.as_deref(),
+            reason,
+        })
+        .await?;
+    Ok((StatusCode::CREATED, Json(suggestion)))
+}
+
+/// List the caller's memory suggestions (pending by default), newest first.
+#[instrument(skip(state, user))]
+pub async fn list_memory_suggestions(
+    State(state): State<AppState>,
+    user: CurrentUser,
+    Query(query): Query<SuggestionListQuery>,
+) -> ApiResult<Json<Vec<MemorySuggestion>>> {
+    let suggestions = promotion_service(&state)?
+        .repository()
+        .list_for_user(user.id(), &query)
+        .await?;
+    Ok(Json(suggestions))
+}
+
+/// Response of accepting a suggestion.
+#[derive(Debug, Serialize)]
+pub struct AcceptSuggestionResponse {
+    pub suggestion: MemorySuggestion,
+    pub memory: PromotedMemory,
+}
+
+/// Accept a pending suggestion, optionally editing it first, and write it
+/// to mmry.
+#[instrument(skip(state, user, edits))]
+pub async fn accept_memory_suggestion(
+    State(state): State<AppState>,
+    user: CurrentUser,
+    Path(suggestion_id): Path<String>,
+    edits: Option<Json<AcceptSuggestionRequest>>,
+) -> ApiResult<Json<AcceptSuggestionResponse>> {
+    let service = promotion_service(&state)?;
+    let suggestion = own_suggestion(service, &suggestion_id, user.id()).await?;
+    if suggestion.status != SuggestionStatus::Pending {
+        return Err(ApiError::conflict("Suggestion was already reviewed"));
+    }
+    let item = service
+        .accepted_item(&suggestion, edits.map(|Json(e)| e).unwrap_or_default())
+        .map_err(|e| ApiError::bad_request(format!("{e:#}")))?;
+
+    let memory = service
+        .accept(&suggestion, &item, user.id())
+        .await?
+        .ok_or_else(|| ApiError::conflict("Suggestion was already reviewed"))?;
+    let suggestion = own_suggestion(service, &suggestion_id, user.id()).await?;
+    Ok(Json(AcceptSuggestionResponse { suggestion, memory }))
+}
+
+/// Decline a pending suggestion.
+#[instrument(skip(state, user))]
+pub async fn reject_memory_suggestion(
+    State(state): State<AppState>,
+    user: CurrentUser,
+    Path(suggestion_id): Path<String>,
+) -> ApiResult<Json<MemorySuggestion>> {
+    let service = promotion_service(&state)?;
+    let suggestion = own_suggestion(service, &suggestion_id, user.id()).await?;
+    if !service.reject(&suggestion).await? {
+        return Err(ApiError::conflict("Suggestion was already reviewed"));
+    }
+    Ok(Json(
+        own_suggestion(service, &suggestion_id, user.id()).await?,
+    ))
+}
@@ -0,0 +1,91 @@
+//! Prometheus scrape endpoint.
+
+use axum::{
+    extract::State,
+    http::{HeaderMap, header},
+    response::IntoResponse,
+};
+use sha2::{Digest, Sha256};
+
+use crate::observability::metrics::MetricsService;
+use crate::session::SessionStatus;
+
+use crate::api::error::{ApiError, ApiResult};
+use crate::api::state::AppState;
+
+/// Whether the scraper sent the configured bearer token. The peer address
+/// proves nothing: reverse proxies connect from loopback.
+fn authorized(service: &MetricsService, headers: &HeaderMap) -> bool {
+    let expected = &service.config().bearer_token;
+    if expected.is_empty() {
+        return false;
+    }
+    headers
+        .get(header::AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "))
+        // Compare digests so the comparison time doesn't depend on the token.
+        .is_some_and(|token| Sha256::digest(token) == Sha256::digest(expected))
+}
+
+/// Backend metrics in the Prometheus text format.
+///
+/// GET /api/metrics (bearer token; not served until one is configured)
+pub async fn metrics(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> ApiResult<impl IntoResponse> {
+    let service = state
+        .metrics
+        .as_deref()
+        .filter(|service| !service.config().bearer_token.is_empty())
+        .ok_or_else(|| ApiError::not_found("Metrics are disabled"))?;
+    if !authorized(service, &headers) {
+        return Err(ApiError::unauthorized("Invalid metrics token"));
+    }
+
+    let sessions_active = state
+        .sessions
+        .list_sessions()
+        .await?
+        .iter()
+        .filter(|s| s.status == SessionStatus::Running)
+        .count();
+    Ok((
+        [(header::CONTENT_TYPE, "text/plain; version=0.0.4")],
+        service.render(sessions_active).await,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::observability::metrics::MetricsConfig;
+
+    fn bearer(token: &str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            header::AUTHORIZATION,
+            format!("Bearer {token}").parse().unwrap(),
+        );
+        headers
+    }
+
+    #[test]
+    fn test_authorized_requires_configured_token() {
+        let open = MetricsService::new(MetricsConfig::default(), None);
+        assert!(!authorized(&open, &HeaderMap::new()));
+        assert!(!authorized(&open, &bearer("")));
+
+        let service = MetricsService::new(
+            MetricsConfig {
+                bearer_token: "scrape".to_string(),
+                ..Default::default()
+            },
+            None,
+        );
+        assert!(authorized(&service, &bearer("scrape")));
+        assert!(!authorized(&service, &bearer("other")));
+        assert!(!authorized(&service, &HeaderMap::new()));
+    }
+}
@@ -7,14 +7,22 @@ use std::time::Duration;
 use axum::{
     Json,
     extract::{Path, Query, State},
+    http::StatusCode,
 };
 use serde::{Deserialize, Serialize};
 use tokio::process::Command;
 use tracing::{instrument, warn};
 
 use crate::auth::CurrentUser;
+use crate::capabilities::{Capability, CapabilityState, Subsystem};
+use crate::db::DegradedNotice;
 use crate::local::LinuxUsersConfig;
+use crate::scheduler::{
+    MissedRuns, ScheduleRunPage, ScheduleRunQuery, ScheduleRunRecord, ScheduleRunReport,
+    ScheduleRunStatus, SchedulerService,
+};
 use crate::session_ui::SessionAutoAttachMode;
+use crate::voice_health::VoiceHealthStatus;
 
 use crate::api::error::{ApiError, ApiResult};
 use crate::api::state::AppState;
@@ -27,9 +35,16 @@ pub struct HealthResponse {
 }
 
 /// Health check endpoint.
-pub async fn health() -> Json<HealthResponse> {
+///
+/// Reports `degraded` (still HTTP 200) while a database integrity problem is unresolved.
+pub async fn health(State(state): State<AppState>) -> Json<HealthResponse> {
+    let status = if state.db_health.is_degraded() {
+        "degraded"
+    } else {
+        "ok"
+    };
     Json(HealthResponse {
-        status: "ok".to_string(),
+        status: status.to_string(),
         version: env!("CARGO_PKG_VERSION").to_string(),
     })
 }
@@ -63,11 +78,39 @@ pub struct FeaturesResponse {
     pub websocket_events: bool,
     /// Whether the agent-browser integration is enabled.
     pub agent_browser_enabled: bool,
+    /// Whether workspaces can be encrypted at rest.
+    pub workspace_encryption_enabled: bool,
+    /// Whether users can request access to other users' workspace directories.
+    pub workspace_access_enabled: bool,
+    /// Whether `GET /api/sessions/{id}/events/poll` is available.
+    pub long_poll_events_enabled: bool,
+    /// Whether users can register without an invite code.
+    pub open_registration: bool,
+    /// Single sign-on through an OpenID Connect provider (null if disabled).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub oidc: Option<OidcFeature>,
+    /// Whether users can sign in with passkeys.
+    pub passkeys: bool,
+    /// Degraded-mode notices (database corruption/restores) for a banner.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub degraded: Vec<DegradedNotice>,
+}
+
+/// Single sign-on exposed to the login page.
+#[derive(Debug, Serialize)]
+pub struct OidcFeature {
+    /// Text of the sign-in button.
+    pub label: String,
+    /// Where the button leads; takes a `return_to` path.
+    pub login_url: String,
 }
 
 /// Voice configuration exposed to frontend.
 #[derive(Debug, Serialize)]
 pub struct VoiceConfig {
+    /// Whether the voice services are reachable; updates arrive as
+    /// `voice.availability` events.
+    pub available: bool,
     /// WebSocket URL for the eaRS STT service.
     pub stt_url: String,
     /// WebSocket URL for the kokorox TTS service.
@@ -103,21 +146,22 @@ pub struct VisualizerVoice {
 
 /// Get enabled features/capabilities.
 pub async fn features(State(state): State<AppState>) -> Json<FeaturesResponse> {
-    let voice = if state.voice.enabled {
+    let voice_state = state.voice();
+    let voice = if voice_state.enabled {
         Some(VoiceConfig {
+            available: state.capabilities.get(Subsystem::Voice).state == CapabilityState::Enabled,
             stt_url: "/api/voice/stt".to_string(),
             tts_url: "/api/voice/tts".to_string(),
-            vad_timeout_ms: state.voice.vad_timeout_ms,
-            default_voice: state.voice.default_voice.clone(),
-            default_speed: state.voice.default_speed,
-            auto_language_detect: state.voice.auto_language_detect,
-            tts_muted: state.voice.tts_muted,
-            continuous_mode: state.voice.continuous_mode,
-            default_visualizer: state.voice.default_visualizer.clone(),
-            interrupt_word_count: state.voice.interrupt_word_count,
-            interrupt_backoff_ms: state.voice.interrupt_backoff_ms,
-            visualizer_voices: state
-                .voice
+            vad_timeout_ms: voice_state.vad_timeout_ms,
+            default_voice: voice_state.default_voice.clone(),
+            default_speed: voice_state.default_speed,
+            auto_language_detect: voice_state.auto_language_detect,
+            tts_muted: voice_state.tts_muted,
+            continuous_mode: voice_state.continuous_mode,
+            default_visualizer: voice_state.default_visualizer.clone(),
+            interrupt_word_count: voice_state.interrupt_word_count,
+            interrupt_backoff_ms: voice_state.interrupt_backoff_ms,
+            visualizer_voices: voice_state
                 .visualizer_voices
                 .iter()
                 .map(|(k, v)| {
@@ -143,9 +187,42 @@ pub async fn features(State(state): State<AppState>) -> Json<FeaturesResponse> {
         // WebSocket events are always enabled when the ws module is compiled in
         websocket_events: true,
         agent_browser_enabled: state.sessions.agent_browser_enabled(),
+        workspace_encryption_enabled: state.workspace_encryption.enabled,
+        workspace_access_enabled: state.workspace_access.is_some(),
+        long_poll_events_enabled: state.session_events.is_some(),
+        open_registration: state.registration.as_ref().is_some_and(|r| r.is_open()),
+        oidc: state.oidc.as_ref().map(|provider| OidcFeature {
+            label: provider.config().label.clone(),
+            login_url: "/api/auth/oidc/login".to_string(),
+        }),
+        passkeys: state.passkeys.is_some(),
+        degraded: state.db_health.notices(),
+    })
+}
+
+/// Response of `GET /api/meta/capabilities`.
+#[derive(Debug, Serialize)]
+pub struct CapabilitiesResponse {
+    pub capabilities: Vec<Capability>,
+}
+
+/// State of the optional subsystems (mmry, voice, hstry, EAVS, sldr), with
+/// the reason for each one that is disabled or degraded.
+pub async fn capabilities(State(state): State<AppState>) -> Json<CapabilitiesResponse> {
+    Json(CapabilitiesResponse {
+        capabilities: state.capabilities.snapshot(),
     })
 }
 
+/// Cached health of the voice services, refreshed by the voice health poller.
+pub async fn voice_health(State(state): State<AppState>) -> ApiResult<Json<VoiceHealthStatus>> {
+    let monitor = state
+        .voice_health
+        .as_ref()
+        .ok_or_else(|| ApiError::service_unavailable("Voice health checks are not running"))?;
+    Ok(Json(monitor.status()))
+}
+
 // ============================================================================
 // Scheduler + Feed handlers
 // ============================================================================
@@ -158,6 +235,11 @@ pub struct SchedulerEntry {
     pub command: String,
     #[serde(default)]
     pub next_run: Option<String>,
+    /// Most recent recorded run (None without run history).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_run: Option<ScheduleRunRecord>,
+    /// Consecutive failed runs up to the latest finished one.
+    pub failure_streak: i64,
 }
 
 #[derive(Debug, Serialize)]
@@ -371,6 +453,8 @@ fn parse_skdlr_list(output: &str) -> Vec<SchedulerEntry> {
             schedule,
             command,
             next_run: None,
+            last_run: None,
+            failure_streak: 0,
         });
     }
 
@@ -434,6 +518,29 @@ pub async fn scheduler_overview(
         }
     }
 
+    if let Some(scheduler) = state.scheduler.as_deref() {
+        let definitions: Vec<(String, String)> = schedules
+            .iter()
+            .map(|s| (s.name.clone(), s.schedule.clone()))
+            .collect();
+        scheduler.sync_schedules(user.id(), &definitions).await;
+
+        let repo = scheduler.repository();
+        let latest = ScheduleRunQuery {
+            limit: Some(1),
+            ..Default::default()
+        };
+        for schedule in &mut schedules {
+            if let Ok((runs, _)) = repo.list(user.id(), &schedule.name, &latest).await {
+                schedule.last_run = runs.into_iter().next();
+            }
+            schedule.failure_streak = repo
+                .failure_streak(user.id(), &schedule.name)
+                .await
+                .unwrap_or_default();
+        }
+    }
+
     let enabled = schedules
         .iter()
         .filter(|s| s.status.eq_ignore_ascii_case("enabled"))
@@ -470,6 +577,141 @@ pub async fn scheduler_delete(
     Ok(Json(serde_json::json!({ "deleted": name })))
 }
 
+fn scheduler_service(state: &AppState) -> ApiResult<&SchedulerService> {
+    state
+        .scheduler
+        .as_deref()
+        .ok_or_else(|| ApiError::service_unavailable("Scheduler run history is disabled"))
+}
+
+/// Run history of a schedule, newest first.
+///
+/// GET /api/schedules/{name}/runs?status=failed&limit=50&offset=0
+#[instrument(skip(state))]
+pub async fn list_schedule_runs(
+    State(state): State<AppState>,
+    user: CurrentUser,
+    Path(name): Path<String>,
+    Query(query): Query<ScheduleRunQuery>,
+) -> ApiResult<Json<ScheduleRunPage>> {
+    let repo = scheduler_service(&state)?.repository();
+    let (runs, total) = repo
+        .list(user.id(), &name, &query)
+        .await
+        .map_err(|e| ApiError::internal(format!("Failed to list schedule runs: {e}")))?;
+    let failure_streak = repo
+        .failure_streak(user.id(), &name)
+        .await
+        .map_err(|e| ApiError::internal(format!("Failed to query failure streak: {e}")))?;
+
+    Ok(Json(ScheduleRunPage {
+        runs,
+        total,
+        limit: query.limit.unwrap_or(50).clamp(1, 500),
+        offset: query.offset.unwrap_or(0).max(0),
+        failure_streak,
+    }))
+}
+
+/// Report a run's start or result. Reporting the same `id` again updates
+/// the run. With priority lanes enabled a new run takes a background slot
+/// and is refused with 429 while the lane is full, so the wrapper can retry
+/// before starting the job.
+///
+/// POST /api/schedules/{name}/runs
+#[instrument(skip(state, report))]
+pub async fn report_schedule_run(
+    State(state): State<AppState>,
+    user: CurrentUser,
+    Path(name): Path<String>,
+    Json(report): Json<ScheduleRunReport>,
+) -> ApiResult<(StatusCode, Json<ScheduleRunRecord>)> {
+    if report.status == ScheduleRunStatus::Skipped {
+        return Err(ApiError::bad_request(
+            "Skipped runs are recorded by the server",
+        ));
+    }
+    let scheduler = scheduler_service(&state)?;
+    let lanes = state.priority_lanes.as_ref();
+    let permit = match (lanes, report.status) {
+        (Some(lanes), ScheduleRunStatus::Running)
+            if !report.id.as_deref().is_some_and(|id| lanes.holds_run(id)) =>
+        {
+            Some(lanes.try_acquire_background().ok_or_else(|| {
+                ApiError::too_many_requests("Background lane is busy, retry later")
+            })?)
+        }
+        _ => None,
+    };
+    let record = scheduler
+        .record_run(user.id(), &name, &report)
+        .await
+        .map_err(|e| ApiError::internal(format!("Failed to record schedule run: {e}")))?
+        .ok_or_else(|| ApiError::conflict("Run ID belongs to another schedule"))?;
+
+    if let Some(lanes) = lanes {
+        match permit {
+            Some(permit) => lanes.hold_run(&record.id, permit),
+            None if record.status != ScheduleRunStatus::Running => lanes.release_run(&record.id),
+            None => {}
+        }
+    }
+    Ok((StatusCode::CREATED, Json(record)))
+}
+
+/// Run each schedule that missed runs during downtime once (`catch_up =
+/// "run_once"`). The catch-up run is recorded like a reported one.
+pub async fn scheduler_catch_up(state: AppState, missed: Vec<MissedRuns>) {
+    let Some(scheduler) = state.scheduler.clone() else {
+        return;
+    };
+    for entry in missed {
+        let _permit = match &state.priority_lanes {
+            Some(lanes) => lanes.acquire_background(&entry.schedule_name).await,
+            None => None,
+        };
+        let started_at = chrono::Utc::now().to_rfc3339();
+        let workspace_root = state.sessions.for_user(&entry.user_id).workspace_root();
+        let result = exec_skdlr_command(
+            &workspace_root,
+            &["run", &entry.schedule_name],
+            state.linux_users.as_ref(),
+            &entry.user_id,
+        )
+        .await;
+        if let Err(e) = &result {
+            warn!(
+                user_id = %entry.user_id,
+                schedule = %entry.schedule_name,
+                "Catch-up run failed: {}",
+                e
+            );
+        }
+        let report = ScheduleRunReport {
+            id: None,
+            status: if result.is_ok() {
+                ScheduleRunStatus::Succeeded
+            } else {
+                ScheduleRunStatus::Failed
+            },
+            scheduled_for: None,
+            started_at: Some(started_at),
+            ended_at: Some(chrono::Utc::now().to_rfc3339()),
+            exit_code: None,
+            log_path: None,
+            cost_usd: None,
+            error: result.err().map(|e| e.to_string()),
+            session_id: None,
+        };
+        if let Err(e) = scheduler
+            .record_run(&entry.user_id, &entry.schedule_name, &report)
+            .await
+        {
+            warn!("Failed to record catch-up run: {e:#}");
+        }
+    }
+}
+
 #[derive(Debug, Deserialize)]
 pub struct FeedFetchQuery {
     pub url: String,
@@ -710,13 +952,7 @@ pub async fn search_sessions(
         }));
     }
 
-    let effective_user = state.effective_linux_username(user.id());
-    let user_home = if state.user_isolation_enabled() {
-        std::path::PathBuf::from(format!("/home/{effective_user}"))
-    } else {
-        dirs::home_dir()
-            .ok_or_else(|| ApiError::internal("could not resolve user home for search"))?
-    };
+    let user_home = search_user_home(&state, user.id())?;
 
     let response = oqto_history::oqto_log::search::search_timeline(
         &oqto_history::oqto_log::search::TimelineSearchRequest {
@@ -726,13 +962,14 @@ pub async fn search_sessions(
             workspace_id: None,
             cwd: None,
             limit: query.limit.max(1),
+            filters: Default::default(),
         },
     )
     .await
     .map_err(|e| ApiError::internal(format!("oqto-log search failed: {e}")))?;
 
-    let hits = response
-        .results
+    let hits = super::private_messages::visible_results(&state, user.id(), response.results)
+        .await?
         .into_iter()
         .map(oqto_log_result_to_search_hit)
         .collect::<Vec<_>>();
@@ -745,6 +982,99 @@ pub async fn search_sessions(
     }))
 }
 
+/// Home directory holding a user's oqto-log databases.
+fn search_user_home(state: &AppState, user_id: &str) -> ApiResult<PathBuf> {
+    if state.user_isolation_enabled() {
+        let effective_user = state.effective_linux_username(user_id);
+        Ok(PathBuf::from(format!("/home/{effective_user}")))
+    } else {
+        dirs::home_dir().ok_or_else(|| ApiError::internal("could not resolve user home for search"))
+    }
+}
+
+/// Most hits returned by one history search.
+const MAX_HISTORY_SEARCH_LIMIT: usize = 200;
+
+/// Query parameters for full-text history search.
+#[derive(Debug, Deserialize)]
+pub struct HistorySearchQuery {
+    pub q: String,
+    /// Session, platform or external ID.
+    #[serde(default)]
+    pub session_id: Option<String>,
+    /// Only sessions in which this agent answered (`model` or `provider/model`).
+    #[serde(default)]
+    pub agent: Option<String>,
+    /// First day (YYYY-MM-DD, UTC), inclusive.
+    #[serde(default)]
+    pub since: Option<String>,
+    /// Last day (YYYY-MM-DD, UTC), inclusive.
+    #[serde(default)]
+    pub until: Option<String>,
+    #[serde(default = "default_search_limit")]
+    pub limit: usize,
+}
+
+/// Response of a history search, best matches first.
+#[derive(Debug, Serialize)]
+pub struct HistorySearchResponse {
+    pub query: String,
+    pub hits: Vec<oqto_history::oqto_log::search::TimelineSearchResult>,
+}
+
+fn checked_day(name: &str, value: Option<&str>) -> ApiResult<Option<String>> {
+    let Some(value) = value.map(str::trim).filter(|v| !v.is_empty()) else {
+        return Ok(None);
+    };
+    chrono::NaiveDate::parse_from_str(value, "%Y-%m-%d")
+        .map(|day| Some(day.format("%Y-%m-%d").to_string()))
+        .map_err(|_| ApiError::bad_request(format!("{name} must be a date (YYYY-MM-DD)")))
+}
+
+/// Full-text search over the current user's chat history, with snippets
+/// and filters by session, agent and date range. Messages other users made
+/// private are not found.
+///
+/// GET /api/history/search?q=...&session_id=...&agent=...&since=YYYY-MM-DD&until=YYYY-MM-DD
+#[instrument(skip(state))]
+pub async fn search_history(
+    State(state): State<AppState>,
+    user: CurrentUser,
+    Query(query): Query<HistorySearchQuery>,
+) -> ApiResult<Json<HistorySearchResponse>> {
+    let since = checked_day("since", query.since.as_deref())?;
+    let until = checked_day("until", query.until.as_deref())?;
+    let session_id = query.session_id.as_deref().filter(|v| !v.trim().is_empty());
+    let agent = query.agent.as_deref().filter(|v| !v.trim().is_empty());
+    let user_home = search_user_home(&state, user.id())?;
+
+    let response = oqto_history::oqto_log::search::search_timeline(
+        &oqto_history::oqto_log::search::TimelineSearchRequest {
+            user_home: &user_home,
+            query: &query.q,
+            scope: oqto_history::oqto_log::search::TimelineSearchScope::All,
+            workspace_id: None,
+            cwd: None,
+            limit: query.limit.clamp(1, MAX_HISTORY_SEARCH_LIMIT),
+            filters: oqto_history::oqto_log::search::TimelineSearchFilters {
+                session_id,
+                agent,
+                since: since.as_deref(),
+                until: until.as_deref(),
+            },
+        },
+    )
+    .await
+    .map_err(|e| ApiError::internal(format!("oqto-log search failed: {e}")))?;
+
+    let hits =
+        super::private_messages::visible_results(&state, user.id(), response.results).await?;
+    Ok(Json(HistorySearchResponse {
+        query: response.query,
+        hits,
+    }))
+}
+
 fn oqto_log_result_to_search_hit(
     hit: oqto_history::oqto_log::search::TimelineSearchResult,
 ) -> SearchHit {
@@ -10,87 +10,255 @@
 //! - `agents`: Agent management
 //! - `agent_rpc`: Agent unified backend API
 //! - `invites`: Invite code management
+//! - `registrations`: Email verification and approval of open registrations
+//! - `roles`: Roles and permissions (RBAC)
 //! - `trx`: TRX issue tracking
 //! - `misc`: Health checks, features, and utilities
+//! - `analytics`: Usage analytics, spend attribution and session tags
+//! - `status`: Public status page and incident notes
+//! - `workspace_access`: Delegated access to other users' workspaces
+//! - `memory`: Promoting session findings into mmry
+//! - `vulnerabilities`: Dependency vulnerability scans of workspaces
+//! - `file_history`: Earlier versions of workspace files
+//! - `git`: Git status, diffs, commits and pushes of workspace repositories
+//! - `outbox`: Review of outbound messages staged by agents
+//! - `approvals`: Approving or denying paused agent tool calls
+//! - `bookmarks`: Named points in session timelines
+//! - `annotations`: Reactions, flags and notes on agent responses
+//! - `private_messages`: Messages kept private to the session owner
+//! - `session_export`: Conversation exports as Markdown, JSON or HTML
+//! - `session_resources`: Live CPU, memory and GPU usage of running sessions
+//! - `image_builds`: Container images built from a session workspace's Dockerfile
+//! - `session_shares`: Sharing sessions with other users
+//! - `session_drafts`: Sessions configured now and started later
+//! - `macros`: User-defined command sequences run against sessions
+//! - `schedules`: Agent prompts run in fresh sessions on a cron schedule
+//! - `triggers`: Sessions started by webhooks and emails
+//! - `webhooks`: Outbound session lifecycle webhooks and their delivery log
+//! - `metrics`: Prometheus scrape endpoint
+//! - `user_env`: Export and import of a user's whole environment
+//! - `workspace_backups`: Incremental workspace snapshots and restores
+//! - `impersonation`: Admins acting as users with their consent
 
 pub(crate) mod admin;
+mod analytics;
+mod annotations;
 mod api_keys;
+mod approvals;
 mod auth;
+mod bookmarks;
 mod chat;
+mod data_query;
 mod feedback;
+mod file_history;
+mod git;
+mod image_builds;
+mod impersonation;
 mod invites;
+mod macros;
+mod memory;
+mod metrics;
 mod misc;
 mod oauth;
+mod outbox;
+pub(crate) mod private_messages;
 mod projects;
+mod registrations;
+mod roles;
+mod schedules;
+mod secrets;
+mod session_drafts;
+mod session_export;
+mod session_resources;
+mod session_shares;
 mod sessions;
 mod settings;
 mod shared_workspaces;
+mod status;
+mod template_tests;
+mod triggers;
 pub mod trx;
+mod user_env;
+pub(crate) mod vulnerabilities;
+mod webhooks;
+mod workspace_access;
+mod workspace_backups;
 
 // Re-export all public types and handlers
 
+// Analytics handlers
+pub use analytics::{
+    admin_get_tag_stats, admin_get_tool_stats, admin_get_usage_summary, admin_list_session_budgets,
+    admin_set_session_budget, get_session_budget, get_session_usage, get_tag_stats, get_tool_stats,
+    get_usage_summary, list_session_tags, run_budget_checks,
+};
+
 // API key handlers
-pub use api_keys::{create_api_key, delete_api_key, list_api_keys, revoke_api_key};
+pub use api_keys::{create_api_key, delete_api_key, get_api_key, list_api_keys, revoke_api_key};
 
 // Session handlers and types
 pub use sessions::{
-    browser_action, check_all_updates, check_session_update, create_session, delete_session,
-    get_or_create_session, get_or_create_session_for_workspace, get_session, list_sessions,
-    resume_session, start_browser, stop_session, touch_session_activity, upgrade_session,
+    browser_action, check_all_updates, check_session_update, clone_session, create_session,
+    delete_session, get_or_create_session, get_or_create_session_for_workspace, get_session,
+    get_session_setup, list_sessions, resume_session, run_blocked_connection_events,
+    run_port_conflict_events, start_browser, stop_session, touch_session_activity, upgrade_session,
 };
 
 // Chat history handlers and types
 pub use chat::{
-    backfill_chat_history, delete_chat_session, get_chat_messages, get_chat_session,
-    list_chat_history, list_chat_history_grouped, update_chat_session,
+    backfill_chat_history, delete_chat_session, get_background_process_logs, get_chat_messages,
+    get_chat_session, get_session_artifact, get_session_artifact_thumbnail,
+    list_background_processes, list_chat_history, list_chat_history_grouped,
+    list_session_artifacts, poll_session_events, start_background_process, stop_background_process,
+    update_chat_session,
 };
 pub use feedback::create_feedback;
 
+// Memory promotion handlers
+pub use memory::{
+    accept_memory_suggestion, list_memory_suggestions, promote_session_memory,
+    reject_memory_suggestion, suggest_session_memory,
+};
+
+// Outbox handlers
+pub use outbox::{
+    approve_outbox_message, get_outbox_message, list_outbox_messages, reject_outbox_message,
+    stage_outbox_message,
+};
+
+// Tool approval handlers
+pub use approvals::{list_session_approvals, resolve_session_approval};
+
+// Session bookmark handlers
+pub use bookmarks::{
+    create_bookmark, delete_bookmark, get_bookmark, list_bookmarks, update_bookmark,
+};
+
+// Message annotation handlers
+pub use annotations::{
+    admin_get_feedback_stats, create_annotation, delete_annotation, get_feedback_stats,
+    list_annotations,
+};
+
+// Private message handlers
+pub use private_messages::{delete_private_message, list_private_messages, mark_message_private};
+
+// Session sharing handlers
+pub use image_builds::build_session_image;
+pub use session_drafts::{
+    create_session_draft, delete_session_draft, get_session_draft, list_session_draft_shares,
+    list_session_drafts, list_session_drafts_shared_with_me, revoke_session_draft_share,
+    run_scheduled_session_drafts, share_session_draft, start_session_draft, update_session_draft,
+};
+pub use session_export::export_session;
+pub use session_resources::{
+    get_session_resources, run_resource_telemetry, update_session_resource_sampling,
+};
+pub use session_shares::{
+    list_session_shares, list_shared_with_me, revoke_session_share, share_session,
+};
+
+// User macro handlers
+pub use macros::{create_macro, delete_macro, get_macro, list_macros, run_macro, update_macro};
+
 // Project handlers and types
 pub use projects::{
-    apply_workspace_pi_resources, create_project_from_template, get_project_logo,
-    get_workspace_meta, get_workspace_pi_resources, get_workspace_sandbox, list_project_templates,
+    apply_workspace_pi_resources, create_project_from_template, create_workspace_from_archive,
+    get_project_logo, get_project_thumbnail, get_workspace_encryption, get_workspace_meta,
+    get_workspace_pi_resources, get_workspace_sandbox, list_project_cards, list_project_templates,
     list_workspace_dirs, list_workspace_locations, set_active_workspace_location,
-    update_workspace_meta, update_workspace_sandbox, upsert_workspace_location,
+    sync_project_templates, update_workspace_encryption, update_workspace_meta,
+    update_workspace_sandbox, upsert_workspace_location,
 };
 
 // Admin handlers and types
 pub use admin::{
-    admin_cleanup_local_sessions, admin_force_stop_session, admin_list_sessions,
-    admin_metrics_stream, get_admin_stats, get_bus_stats, publish_bus_event,
+    admin_cleanup_local_sessions, admin_download_crash_bundle, admin_force_stop_session,
+    admin_list_crash_bundles, admin_list_sessions, admin_metrics_stream, admin_query_audit_log,
+    admin_reload_config, admin_set_session_resource_limits, get_admin_overview, get_admin_stats,
+    get_bus_stats, get_priority_lanes, get_proxy_transfers, get_reconnect_stats, get_runner_hosts,
+    get_siem_health, publish_bus_event,
 };
 
 // User management (admin)
 pub use admin::{
     activate_user, catalog_lookup, create_user, deactivate_user, delete_eavs_provider, delete_user,
-    get_user, get_user_stats, list_eavs_providers, list_users, sync_all_models, sync_user_configs,
-    update_user, upsert_eavs_provider,
+    delete_user_resource_limits, get_disk_usage, get_user, get_user_resource_limits,
+    get_user_stats, list_eavs_providers, list_users, set_user_quota, set_user_resource_limits,
+    sync_all_models, sync_user_configs, update_user, upsert_eavs_provider,
 };
 
 // OAuth handlers
 pub use oauth::{oauth_callback, oauth_delete, oauth_login, oauth_poll, oauth_providers};
 
 // Auth handlers and types
-pub use auth::{change_password, dev_login, get_me, login, logout, register, update_me};
+pub use auth::{
+    change_password, delete_passkey, dev_login, finish_passkey_login, finish_passkey_registration,
+    get_me, list_passkeys, login, logout, oidc_callback, oidc_device_start, oidc_device_token,
+    oidc_login, register, start_passkey_login, start_passkey_registration, update_me,
+};
 
 // Settings handlers and types
 pub use settings::{
-    get_settings_schema, get_settings_values, reload_settings, update_settings_values,
+    get_settings_document, get_settings_history, get_settings_schema, get_settings_sync,
+    get_settings_values, patch_settings_document, reload_settings, sync_settings,
+    update_settings_values,
 };
 
 // Invite code handlers and types
 pub use invites::{
-    create_invite_code, create_invite_codes_batch, delete_invite_code, get_invite_code,
-    get_invite_code_stats, list_invite_codes, revoke_invite_code,
+    create_invite_code, create_invite_codes_batch, delete_invite_code, export_invite_codes,
+    get_invite_code, get_invite_code_stats, import_invite_codes, list_invite_code_redemptions,
+    list_invite_codes, revoke_invite_code, revoke_invite_codes_batch,
+};
+
+// Open registration handlers
+pub use registrations::{
+    approve_registration, list_registrations, reject_registration, resend_verification_email,
+    verify_email,
+};
+
+// Role handlers
+pub use roles::{
+    create_role, delete_role, get_my_permissions, get_user_roles, list_roles, set_user_roles,
+    update_role,
 };
 
 // TRX handlers and types
 pub use trx::{close_trx_issue, create_trx_issue, list_trx_issues, sync_trx, update_trx_issue};
 
+// Prometheus metrics
+pub use metrics::metrics;
+
 // Misc handlers and types
 pub use misc::{
-    codexbar_usage, features, fetch_feed, health, scheduler_delete, scheduler_overview,
-    search_sessions, ws_debug,
+    capabilities, codexbar_usage, features, fetch_feed, health, list_schedule_runs,
+    report_schedule_run, scheduler_catch_up, scheduler_delete, scheduler_overview, search_history,
+    search_sessions, voice_health, ws_debug,
+};
+
+// Agent schedule handlers
+pub use schedules::{
+    create_agent_schedule, delete_agent_schedule, get_agent_schedule, list_agent_schedules,
+    run_agent_schedule, run_agent_schedules, update_agent_schedule,
+};
+
+// Inbound trigger handlers
+pub use triggers::{
+    create_trigger, delete_trigger, fire_trigger, get_trigger, list_trigger_runs, list_triggers,
+    rotate_trigger_token, run_trigger_mail_poller, update_trigger,
+};
+
+// Outbound webhook handlers
+pub use webhooks::{
+    admin_list_webhook_deliveries, admin_list_webhooks, admin_retry_webhook_delivery,
+    run_session_webhooks,
+};
+
+// Status page handlers
+pub use status::{
+    admin_create_incident, admin_delete_incident, admin_list_incidents, admin_update_incident,
+    status,
 };
 
 // Shared workspace handlers
@@ -102,6 +270,55 @@ pub use shared_workspaces::{
     get_shared_workspace, list_shared_workspace_members, list_shared_workspace_workdirs,
     list_shared_workspaces, remove_shared_workspace_member, transfer_shared_workspace_ownership,
     update_shared_workspace, update_shared_workspace_member,
+    update_shared_workspace_member_permissions,
+};
+
+// Vulnerability scan handlers
+pub use vulnerabilities::{
+    get_workspace_vulnerabilities, list_workspace_vulnerability_scans,
+    scan_workspace_vulnerabilities,
+};
+
+// Workspace file history handlers
+pub use file_history::{get_workspace_file, list_workspace_file_versions};
+
+// Workspace git handlers
+pub use git::{
+    commit_workspace_git, delete_git_credential, get_workspace_git_diff, get_workspace_git_log,
+    get_workspace_git_status, list_git_credentials, list_workspace_git_branches,
+    push_workspace_git, set_git_credential, switch_workspace_git_branch,
+};
+
+// Agent secrets handlers
+pub use secrets::{create_secret, delete_secret, get_secret, list_secrets, update_secret};
+
+// Workspace data query handlers
+pub use data_query::query_workspace_data;
+pub use template_tests::{list_template_tests, test_template};
+
+// Delegated workspace access handlers
+pub use workspace_access::{
+    approve_workspace_access, deny_workspace_access, list_workspace_access_grants,
+    request_workspace_access, revoke_workspace_access,
+};
+
+// User environment export/import handlers
+pub use user_env::{
+    admin_export_user_environment, admin_import_user_environment, export_my_environment,
+    import_my_environment,
+};
+
+// Workspace backup handlers
+pub use workspace_backups::{
+    create_workspace_backup, delete_workspace_backup, get_workspace_backup, list_workspace_backups,
+    prune_workspace_backups, restore_workspace_backup, run_workspace_backups,
+};
+
+// Impersonation handlers
+pub use impersonation::{
+    admin_end_impersonation, admin_issue_impersonation_token, admin_list_impersonations,
+    approve_impersonation, deny_impersonation, end_my_impersonation, get_impersonation_activity,
+    list_my_impersonations, request_impersonation,
 };
 
 // Internal helpers used by other modules
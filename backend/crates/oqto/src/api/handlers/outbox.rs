@@ -0,0 +1,166 @@
+//! Outbox handlers: agents stage outbound messages, users review them.
+
+use axum::{
+    Json,
+    extract::{Path, Query, State},
+    http::StatusCode,
+};
+use tracing::instrument;
+
+use crate::auth::CurrentUser;
+use crate::outbox::{
+    ApproveMessageRequest, ApproveOutcome, OutboxListQuery, OutboxMessage, OutboxService,
+    OutboxStatus, RejectMessageRequest, StageMessageRequest,
+};
+
+use super::chat::{SessionArtifactQuery, session_runner};
+use crate::api::error::{ApiError, ApiResult};
+use crate::api::state::AppState;
+
+/// Longest accepted reason or review note.
+const MAX_REASON_LEN: usize = 500;
+
+fn outbox_service(state: &AppState) -> ApiResult<&OutboxService> {
+    state
+        .outbox
+        .as_deref()
+        .ok_or_else(|| ApiError::not_found("Outbox is disabled"))
+}
+
+/// Load a message of the caller. Other users' messages are reported as
+/// missing.
+async fn own_message(
+    service: &OutboxService,
+    message_id: &str,
+    user_id: &str,
+) -> ApiResult<OutboxMessage> {
+    service
+        .repository()
+        .get(message_id)
+        .await?
+        .filter(|m| m.user_id == user_id)
+        .ok_or_else(|| ApiError::not_found(format!("Message {message_id} not found")))
+}
+
+/// Trimmed optional text, at most `MAX_REASON_LEN` bytes.
+fn short_text<'a>(text: Option<&'a str>, field: &str) -> ApiResult<Option<&'a str>> {
+    let text = text.map(str::trim).filter(|t| !t.is_empty());
+    if text.is_some_and(|t| t.len() > MAX_REASON_LEN) {
+        return Err(ApiError::bad_request(format!(
+            "{field} must be at most {MAX_REASON_LEN} characters"
+        )));
+    }
+    Ok(text)
+}
+
+/// Stage a message for the user's approval.
+///
+/// Agents call this through `oqtoctl outbox`. Nothing is sent until the
+/// user approves the message.
+#[instrument(skip(state, user, request))]
+pub async fn stage_outbox_message(
+    State(state): State<AppState>,
+    user: CurrentUser,
+    Path(session_id): Path<String>,
+    Query(query): Query<SessionArtifactQuery>,
+    Json(mut request): Json<StageMessageRequest>,
+) -> ApiResult<(StatusCode, Json<OutboxMessage>)> {
+    let service = outbox_service(&state)?;
+    service
+        .validate(request.channel, &request.content)
+        .map_err(|e| ApiError::bad_request(format!("{e:#}")))?;
+    request.reason = short_text(request.reason.as_deref(), "reason")?.map(str::to_string);
+
+    let runner = session_runner(
+        &state,
+        user.id(),
+        &session_id,
+        query.shared_workspace_id.as_deref(),
+    )
+    .await?;
+    runner
+        .get_workspace_chat_session(&session_id)
+        .await
+        .map_err(|e| ApiError::internal(format!("runner get session failed: {e:#}")))?
+        .session
+        .ok_or_else(|| ApiError::not_found(format!("Session {session_id} not found")))?;
+
+    if service.repository().count_pending(&session_id).await?
+        >= service.config().max_pending_per_session
+    {
+        return Err(ApiError::too_many_requests(
+            "Too many pending outbound messages for this session",
+        ));
+    }
+
+    let message = service.stage(user.id(), &session_id, &request).await?;
+    Ok((StatusCode::CREATED, Json(message)))
+}
+
+/// List the caller's outbound messages (pending by default), newest first.
+#[instrument(skip(state, user))]
+pub async fn list_outbox_messages(
+    State(state): State<AppState>,
+    user: CurrentUser,
+    Query(query): Query<OutboxListQuery>,
+) -> ApiResult<Json<Vec<OutboxMessage>>> {
+    let messages = outbox_service(&state)?
+        .repository()
+        .list_for_user(user.id(), &query)
+        .await?;
+    Ok(Json(messages))
+}
+
+/// Full content of one outbound message.
+#[instrument(skip(state, user))]
+pub async fn get_outbox_message(
+    State(state): State<AppState>,
+    user: CurrentUser,
+    Path(message_id): Path<String>,
+) -> ApiResult<Json<OutboxMessage>> {
+    let service = outbox_service(&state)?;
+    Ok(Json(own_message(service, &message_id, user.id()).await?))
+}
+
+/// Approve a pending (or failed) message, optionally editing it first, and
+/// send it. The response carries the delivery result: `sent`, or `failed`
+/// with `error` set.
+#[instrument(skip(state, user, edits))]
+pub async fn approve_outbox_message(
+    State(state): State<AppState>,
+    user: CurrentUser,
+    Path(message_id): Path<String>,
+    edits: Option<Json<ApproveMessageRequest>>,
+) -> ApiResult<Json<OutboxMessage>> {
+    let service = outbox_service(&state)?;
+    let message = own_message(service, &message_id, user.id()).await?;
+    if !matches!(message.status, OutboxStatus::Pending | OutboxStatus::Failed) {
+        return Err(ApiError::conflict("Message was already reviewed"));
+    }
+    let content = service
+        .approved_content(&message, edits.map(|Json(e)| e).unwrap_or_default())
+        .map_err(|e| ApiError::bad_request(format!("{e:#}")))?;
+
+    match service.approve(&message, &content, user.id()).await? {
+        ApproveOutcome::Delivered(message) => Ok(Json(message)),
+        ApproveOutcome::AlreadyReviewed => Err(ApiError::conflict("Message was already reviewed")),
+    }
+}
+
+/// Decline a pending or failed message.
+#[instrument(skip(state, user, request))]
+pub async fn reject_outbox_message(
+    State(state): State<AppState>,
+    user: CurrentUser,
+    Path(message_id): Path<String>,
+    request: Option<Json<RejectMessageRequest>>,
+) -> ApiResult<Json<OutboxMessage>> {
+    let service = outbox_service(&state)?;
+    let message = own_message(service, &message_id, user.id()).await?;
+    let request = request.map(|Json(r)| r).unwrap_or_default();
+    let note = short_text(request.note.as_deref(), "note")?;
+    if !service.reject(&message, user.id(), note).await? {
+        return Err(ApiError::conflict("Message was already reviewed"));
+    }
+    Ok(Json(own_message(service, &message_id, user.id()).await?))
+}
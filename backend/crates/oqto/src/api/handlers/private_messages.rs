@@ -0,0 +1,311 @@
+//! Private message handlers: keeping messages and parts of readable sessions
+//! to the session owner.
+
+use axum::{
+    Json,
+    extract::{Path, Query, State},
+    http::StatusCode,
+};
+use oqto_history::oqto_log::search::TimelineSearchResult;
+use oqto_runner::client::RunnerClient;
+use oqto_runner::protocol::WorkspaceChatMessagesSource;
+use tracing::{info, instrument, warn};
+
+use crate::auth::CurrentUser;
+use crate::private_messages::{
+    MarkPrivateRequest, NewPrivateMessage, PrivateMessage, PrivateMessageRepository,
+};
+use crate::shared_workspace::SharePermission;
+use crate::ws::types::WsEvent;
+
+use super::bookmarks::{require_chat_read, session_access};
+use super::chat::{SessionArtifactQuery, session_runner};
+use super::impersonation::refuse_impersonated;
+use crate::api::error::{ApiError, ApiResult};
+use crate::api::state::AppState;
+
+/// Most private messages and parts in one session.
+const MAX_PRIVATE_PER_SESSION: i64 = 1000;
+
+fn private_messages(state: &AppState) -> ApiResult<&PrivateMessageRepository> {
+    state
+        .private_messages
+        .as_deref()
+        .ok_or_else(|| ApiError::not_found("Private messages are not available"))
+}
+
+/// Messages and parts of a session that `viewer` may not read.
+pub(crate) async fn hidden_from(
+    state: &AppState,
+    session_id: &str,
+    viewer: &str,
+) -> anyhow::Result<Vec<PrivateMessage>> {
+    match &state.private_messages {
+        Some(repo) => repo.hidden_from(session_id, viewer).await,
+        None => Ok(Vec::new()),
+    }
+}
+
+/// Tool calls of a session whose attachments are hidden from `viewer`: those
+/// in the messages and parts hidden from them.
+pub(crate) async fn hidden_tool_calls(
+    state: &AppState,
+    runner: &RunnerClient,
+    session_id: &str,
+    viewer: &str,
+) -> ApiResult<Vec<String>> {
+    let hidden = hidden_from(state, session_id, viewer).await?;
+    tool_calls_in(runner, session_id, &hidden).await
+}
+
+/// Tool calls in the `hidden` messages and parts of a session.
+pub(crate) async fn tool_calls_in(
+    runner: &RunnerClient,
+    session_id: &str,
+    hidden: &[PrivateMessage],
+) -> ApiResult<Vec<String>> {
+    if hidden.is_empty() {
+        return Ok(Vec::new());
+    }
+    let messages = runner
+        .get_workspace_chat_session_messages(
+            session_id,
+            false,
+            None,
+            WorkspaceChatMessagesSource::Authoritative,
+        )
+        .await
+        .map_err(|e| ApiError::internal(format!("runner get messages failed: {e:#}")))?
+        .messages;
+    Ok(crate::private_messages::hidden_tool_calls(
+        &messages, hidden,
+    ))
+}
+
+/// Drop search results from messages hidden from `viewer`.
+pub(crate) async fn visible_results(
+    state: &AppState,
+    viewer: &str,
+    results: Vec<TimelineSearchResult>,
+) -> anyhow::Result<Vec<TimelineSearchResult>> {
+    let mut hidden: std::collections::HashMap<String, Vec<PrivateMessage>> =
+        std::collections::HashMap::new();
+    let mut visible = Vec::with_capacity(results.len());
+    for result in results {
+        let session_id = if result.platform_id.is_empty() {
+            &result.session_id
+        } else {
+            &result.platform_id
+        };
+        if !hidden.contains_key(session_id) {
+            let marks = hidden_from(state, session_id, viewer).await?;
+            hidden.insert(session_id.clone(), marks);
+        }
+        if !crate::private_messages::is_hidden(&hidden[session_id], &result.message_id) {
+            visible.push(result);
+        }
+    }
+    Ok(visible)
+}
+
+/// Check that the caller owns the session: personal sessions are the
+/// caller's own, shared workspace sessions record who started them. Returns
+/// the session's shared workspace.
+async fn require_owner(
+    state: &AppState,
+    user_id: &str,
+    session_id: &str,
+    shared_workspace_id: Option<&str>,
+) -> ApiResult<Option<String>> {
+    let workspace_id = session_access(state, user_id, session_id, shared_workspace_id).await?;
+    if workspace_id.is_some() {
+        let owner = state
+            .session_targets
+            .get(session_id)
+            .await?
+            .and_then(|record| record.owner_user_id);
+        if owner.as_deref() != Some(user_id) {
+            return Err(ApiError::forbidden(
+                "Only the session owner can make messages private",
+            ));
+        }
+    }
+    Ok(workspace_id)
+}
+
+/// Tell everyone who can read the session that what they may see changed:
+/// the owner, members of the shared workspace with `chat_read` and the
+/// users the session is shared with.
+async fn publish(state: &AppState, mark: &PrivateMessage, private: bool) {
+    let mut recipients = vec![mark.owner_user_id.clone()];
+    if let (Some(workspace_id), Some(service)) = (&mark.workspace_id, &state.shared_workspaces) {
+        match service
+            .list_members(workspace_id, &mark.owner_user_id)
+            .await
+        {
+            Ok(members) => recipients.extend(
+                members
+                    .into_iter()
+                    .filter(|m| m.permissions.allows(SharePermission::ChatRead))
+                    .map(|m| m.user_id),
+            ),
+            Err(e) => warn!(
+                workspace_id = %workspace_id,
+                "Failed to list workspace members for private message event: {e:#}"
+            ),
+        }
+    }
+    if let Some(shares) = &state.session_shares {
+        match shares.list_for_session(&mark.session_id).await {
+            Ok(shares) => recipients.extend(shares.into_iter().map(|share| share.user_id)),
+            Err(e) => warn!(
+                session_id = %mark.session_id,
+                "Failed to list session shares for private message event: {e:#}"
+            ),
+        }
+    }
+    recipients.sort();
+    recipients.dedup();
+    for user_id in recipients {
+        state
+            .ws_hub
+            .send_to_user(
+                &user_id,
+                WsEvent::MessagePrivate {
+                    session_id: mark.session_id.clone(),
+                    message_id: mark.message_id.clone(),
+                    part_id: mark.part_id.clone(),
+                    private,
+                },
+            )
+            .await;
+    }
+}
+
+/// Make a message, or one part of it, private to the session owner.
+/// Everyone else who reads the session gets a placeholder instead.
+#[instrument(skip(state, user, request))]
+pub async fn mark_message_private(
+    State(state): State<AppState>,
+    user: CurrentUser,
+    Path(session_id): Path<String>,
+    Query(query): Query<SessionArtifactQuery>,
+    Json(request): Json<MarkPrivateRequest>,
+) -> ApiResult<(StatusCode, Json<PrivateMessage>)> {
+    refuse_impersonated(&user, "Messages cannot be made private")?;
+    let repo = private_messages(&state)?;
+    let part_id = request
+        .part_id
+        .as_deref()
+        .map(str::trim)
+        .filter(|id| !id.is_empty());
+    let workspace_id = require_owner(
+        &state,
+        user.id(),
+        &session_id,
+        query.shared_workspace_id.as_deref(),
+    )
+    .await?;
+    if repo.count_for_session(&session_id).await? >= MAX_PRIVATE_PER_SESSION {
+        return Err(ApiError::too_many_requests(
+            "Too many private messages in this session",
+        ));
+    }
+
+    let runner = session_runner(&state, user.id(), &session_id, workspace_id.as_deref()).await?;
+    let message = runner
+        .get_workspace_chat_session_messages(
+            &session_id,
+            false,
+            None,
+            WorkspaceChatMessagesSource::Authoritative,
+        )
+        .await
+        .map_err(|e| ApiError::internal(format!("runner get messages failed: {e:#}")))?
+        .messages
+        .into_iter()
+        .find(|m| m.id == request.message_id)
+        .ok_or_else(|| ApiError::not_found(format!("Message {} not found", request.message_id)))?;
+    if let Some(part_id) = part_id
+        && !message.parts.iter().any(|p| p.id == part_id)
+    {
+        return Err(ApiError::not_found(format!(
+            "Part {part_id} not found in message {}",
+            request.message_id
+        )));
+    }
+
+    let mark = repo
+        .mark(&NewPrivateMessage {
+            session_id: &session_id,
+            message_id: &message.id,
+            part_id,
+            owner_user_id: user.id(),
+            workspace_id: workspace_id.as_deref(),
+        })
+        .await?;
+    info!(
+        session_id = %session_id,
+        message_id = %mark.message_id,
+        part_id = ?mark.part_id,
+        "Made message private"
+    );
+    publish(&state, &mark, true).await;
+    Ok((StatusCode::CREATED, Json(mark)))
+}
+
+/// Private messages and parts of a session, oldest first.
+#[instrument(skip(state, user))]
+pub async fn list_private_messages(
+    State(state): State<AppState>,
+    user: CurrentUser,
+    Path(session_id): Path<String>,
+    Query(query): Query<SessionArtifactQuery>,
+) -> ApiResult<Json<Vec<PrivateMessage>>> {
+    let repo = private_messages(&state)?;
+    session_access(
+        &state,
+        user.id(),
+        &session_id,
+        query.shared_workspace_id.as_deref(),
+    )
+    .await?;
+    Ok(Json(repo.list_for_session(&session_id).await?))
+}
+
+/// Make a private message or part readable again. Only the session owner
+/// can.
+#[instrument(skip(state, user))]
+pub async fn delete_private_message(
+    State(state): State<AppState>,
+    user: CurrentUser,
+    Path(private_message_id): Path<String>,
+) -> ApiResult<StatusCode> {
+    refuse_impersonated(&user, "Messages cannot be made readable")?;
+    let repo = private_messages(&state)?;
+    let not_found =
+        || ApiError::not_found(format!("Private message {private_message_id} not found"));
+    let mark = repo.get(&private_message_id).await?.ok_or_else(not_found)?;
+    if mark.owner_user_id != user.id() {
+        return match &mark.workspace_id {
+            Some(workspace_id) => {
+                require_chat_read(&state, user.id(), workspace_id)
+                    .await
+                    .map_err(|_| not_found())?;
+                Err(ApiError::forbidden(
+                    "Only the session owner can make messages readable again",
+                ))
+            }
+            None => Err(not_found()),
+        };
+    }
+    repo.delete(&mark.id).await?;
+    info!(
+        session_id = %mark.session_id,
+        message_id = %mark.message_id,
+        part_id = ?mark.part_id,
+        "Made message readable again"
+    );
+    publish(&state, &mark, false).await;
+    Ok(StatusCode::NO_CONTENT)
+}
@@ -7,21 +7,30 @@ use std::path::{Path, PathBuf};
 use anyhow::Context;
 use axum::{
     Json,
+    body::Bytes,
     extract::{Query, State},
+    http::StatusCode,
     response::IntoResponse,
 };
+use base64::Engine;
 use serde::{Deserialize, Serialize};
 use serde_json::{Value, json};
 use tokio::process::Command;
 use tracing::instrument;
 use uuid::Uuid;
 
-use crate::api::handlers::trx::validate_workspace_path;
-use crate::auth::CurrentUser;
-use crate::projects::{self, ProjectMetadata};
-use crate::session::WorkspaceLocationInput;
+use crate::api::handlers::sessions::SessionWithUrls;
+use crate::api::handlers::trx::{validate_workspace_path, validated_runner};
+use crate::auth::{Access, CurrentUser, Permission};
+use crate::projects::{self, ProjectMetadata, TemplateVariable};
+use crate::runner::router::{ExecutionTarget, resolve_runner_for_target};
+use crate::session::{CreateSessionRequest, WorkspaceLocationInput};
 use crate::settings::{ConfigUpdate, SettingsScope};
 use crate::workspace::meta::{WorkspaceMeta, load_workspace_meta, write_workspace_meta};
+use oqto_runner::protocol::{
+    ArchiveFormat, ErrorCode, ErrorResponse, ExtractArchiveRequest, Passphrase,
+    WorkspaceEncryptionAction, WorkspaceEncryptionState,
+};
 use oqto_sandbox::{SandboxConfigFile, SandboxProfile};
 
 use crate::api::error::{ApiError, ApiResult};
@@ -63,6 +72,9 @@ pub struct ProjectTemplateEntry {
     pub description: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub defaults: Option<ProjectTemplateDefaults>,
+    /// Values to ask for when creating a project from the template.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub variables: Vec<TemplateVariable>,
 }
 
 /// Response for listing project templates.
@@ -74,6 +86,13 @@ pub struct ListProjectTemplatesResponse {
     pub templates: Vec<ProjectTemplateEntry>,
 }
 
+/// Response for syncing the templates repository.
+#[derive(Debug, Serialize)]
+pub struct SyncProjectTemplatesResponse {
+    /// Whether the repository was pulled (false for local or non-git repos).
+    pub synced: bool,
+}
+
 /// Request to create a project from a template.
 #[derive(Debug, Deserialize)]
 pub struct CreateProjectFromTemplateRequest {
@@ -83,6 +102,9 @@ pub struct CreateProjectFromTemplateRequest {
     pub shared: bool,
     /// When set, the project is created inside this shared workspace.
     pub shared_workspace_id: Option<String>,
+    /// Values of the template's variables; defaults fill in the rest.
+    #[serde(default)]
+    pub variables: HashMap<String, String>,
 }
 
 /// Query for listing workspace locations.
@@ -205,7 +227,7 @@ fn find_project_logo(project_path: &std::path::Path, project_name: &str) -> Opti
     })
 }
 
-fn sanitize_relative_path(raw: &str) -> Result<PathBuf, ApiError> {
+pub(crate) fn sanitize_relative_path(raw: &str) -> Result<PathBuf, ApiError> {
     let trimmed = raw.trim();
     if trimmed.is_empty() {
         return Err(ApiError::bad_request("path is required"));
@@ -364,6 +386,92 @@ fn copy_template_dir(src: &std::path::Path, dest: &std::path::Path) -> Result<()
     Ok(())
 }
 
+/// Render a template with variables into a temporary directory to create
+/// the project from; None when it declares none and is copied as it is.
+pub(super) fn stage_template(
+    template_dir: &Path,
+    values: &HashMap<String, String>,
+) -> Result<Option<tempfile::TempDir>, ApiError> {
+    if values.is_empty() {
+        return Ok(None);
+    }
+    let staging = tempfile::tempdir()
+        .map_err(|e| ApiError::internal(format!("Failed to create staging dir: {e}")))?;
+    projects::render_template(template_dir, &staging.path().join("template"), values)
+        .map_err(|e| ApiError::bad_request(format!("Failed to render template: {e:#}")))?;
+    Ok(Some(staging))
+}
+
+/// Root new projects are created under: the user's workspace root, or a
+/// shared workspace the user is a member of, with the Linux user owning it.
+pub(super) async fn project_root(
+    state: &AppState,
+    user_id: &str,
+    shared_workspace_id: Option<&str>,
+) -> Result<(PathBuf, Option<String>), ApiError> {
+    let Some(sw_id) = shared_workspace_id else {
+        return Ok((state.sessions.for_user(user_id).workspace_root(), None));
+    };
+    // Verify user is a member of this shared workspace.
+    let sw_service = state
+        .shared_workspaces
+        .as_ref()
+        .ok_or_else(|| ApiError::bad_request("shared workspaces not available"))?;
+    let workspaces = sw_service
+        .list_for_user(user_id)
+        .await
+        .map_err(|e| ApiError::internal(format!("Failed to list shared workspaces: {e}")))?;
+    let ws = workspaces
+        .iter()
+        .find(|w| w.id == sw_id)
+        .ok_or_else(|| ApiError::bad_request("shared workspace not found or not a member"))?;
+    Ok((PathBuf::from(&ws.path), Some(ws.linux_user.clone())))
+}
+
+/// Copy `template_dir` to `target_dir` in a workspace. In multi-user mode
+/// usermgr does it (runs as root, can write to user homes), as
+/// `linux_username_override` or the user's own Linux account.
+pub(super) fn create_from_template(
+    state: &AppState,
+    user_id: &str,
+    linux_username_override: Option<&str>,
+    workspace_root: &Path,
+    template_dir: &Path,
+    target_dir: &Path,
+) -> Result<(), ApiError> {
+    let Some(linux_users) = state.linux_users.as_ref().filter(|lu| lu.enabled) else {
+        // Single-user mode: create directly.
+        if !workspace_root.exists() {
+            fs::create_dir_all(workspace_root).map_err(|e| {
+                ApiError::internal(format!(
+                    "Failed to create workspace directory {:?}: {}",
+                    workspace_root, e
+                ))
+            })?;
+        }
+        if target_dir.exists() {
+            return Err(ApiError::bad_request("project path already exists"));
+        }
+        return copy_template_dir(template_dir, target_dir);
+    };
+    let linux_username = linux_username_override
+        .map(str::to_string)
+        .unwrap_or_else(|| linux_users.linux_username(user_id));
+    let target_str = target_dir
+        .to_str()
+        .ok_or_else(|| ApiError::internal("invalid target path"))?;
+    crate::local::linux_users::usermgr_request(
+        "create-workspace",
+        serde_json::json!({
+            "username": linux_username,
+            "path": target_str,
+            "template_src": template_dir.to_string_lossy(),
+        }),
+    )
+    .map_err(|e| ApiError::internal(format!("Failed to create project dir: {e}")))?;
+    Ok(())
+}
+
 /// List directories under the workspace root (projects view).
 #[instrument(skip(state))]
 pub async fn list_workspace_dirs(
@@ -428,6 +536,76 @@ pub async fn list_workspace_dirs(
     Ok(Json(dirs))
 }
 
+/// Card data for the project grid: one entry per project directory at the
+/// workspace root, like `GET /api/projects`.
+#[instrument(skip(state, user))]
+pub async fn list_project_cards(
+    State(state): State<AppState>,
+    user: CurrentUser,
+) -> ApiResult<Json<Vec<crate::project_cards::ProjectCard>>> {
+    let service = state
+        .project_cards
+        .as_ref()
+        .ok_or_else(|| ApiError::service_unavailable("Project cards are not available"))?;
+    let root = state.sessions.for_user(user.id()).workspace_root();
+
+    let mut dirs = Vec::new();
+    if let Ok(entries) = std::fs::read_dir(&root) {
+        for entry in entries.flatten() {
+            let path = entry.path();
+            let hidden = entry.file_name().to_string_lossy().starts_with('.');
+            if !hidden && path.is_dir() {
+                dirs.push(path.canonicalize().unwrap_or(path));
+            }
+        }
+    }
+
+    let cards = futures::future::join_all(dirs.iter().map(|dir| service.card(dir))).await;
+    let mut cards = cards
+        .into_iter()
+        .collect::<anyhow::Result<Vec<_>>>()
+        .map_err(|e| ApiError::internal(format!("Failed to build project cards: {}", e)))?;
+    cards.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(Json(cards))
+}
+
+/// Thumbnail image of a project card.
+#[instrument(skip(state, user, query))]
+pub async fn get_project_thumbnail(
+    State(state): State<AppState>,
+    user: CurrentUser,
+    Query(query): Query<WorkspaceMetaQuery>,
+) -> Result<impl IntoResponse, ApiError> {
+    use crate::project_cards::{MAX_THUMBNAIL_BYTES, thumbnail, thumbnail_content_type};
+    use axum::http::header;
+
+    let workspace_root = validate_workspace_path(&state, user.id(), &query.workspace_path).await?;
+    let Some(relative) = thumbnail(&workspace_root) else {
+        return Err(ApiError::not_found("Project has no thumbnail"));
+    };
+    let content_type = thumbnail_content_type(&relative).unwrap_or("application/octet-stream");
+    let full_path = workspace_root.join(&relative);
+
+    let size = tokio::fs::metadata(&full_path)
+        .await
+        .map_err(|e| ApiError::internal(format!("Failed to read thumbnail: {}", e)))?
+        .len();
+    if size > MAX_THUMBNAIL_BYTES {
+        return Err(ApiError::bad_request("Thumbnail is too large"));
+    }
+    let contents = tokio::fs::read(&full_path)
+        .await
+        .map_err(|e| ApiError::internal(format!("Failed to read thumbnail: {}", e)))?;
+
+    Ok((
+        [
+            (header::CONTENT_TYPE, content_type),
+            (header::CACHE_CONTROL, "private, max-age=300"),
+        ],
+        contents,
+    ))
+}
+
 /// List available project templates from the templates repository.
 #[instrument(skip(state))]
 pub async fn list_project_templates(
@@ -468,11 +646,16 @@ pub async fn list_project_templates(
             .to_string();
         let description = read_template_description(&path);
         let defaults = read_template_defaults(&path);
+        let variables = projects::read_variables(&path).unwrap_or_else(|e| {
+            tracing::warn!(template = %name, "Ignoring template variables: {e:#}");
+            Vec::new()
+        });
         templates.push(ProjectTemplateEntry {
             name,
             path: rel,
             description,
             defaults,
+            variables,
         });
     }
     templates.sort_by(|a, b| a.name.cmp(&b.name));
@@ -482,7 +665,26 @@ pub async fn list_project_templates(
     }))
 }
 
-/// Create a new project from a template.
+/// Pull the templates repository now (admin or `templates.manage`).
+#[instrument(skip(state, access))]
+pub async fn sync_project_templates(
+    State(state): State<AppState>,
+    access: Access,
+) -> ApiResult<Json<SyncProjectTemplatesResponse>> {
+    access.require(Permission::TemplatesManage)?;
+    if state.templates.repo_path.is_none() {
+        return Err(ApiError::not_found("No templates repository configured"));
+    }
+    let synced = state
+        .templates
+        .sync_now()
+        .await
+        .map_err(|e| ApiError::internal(format!("Failed to sync templates: {:#}", e)))?;
+    Ok(Json(SyncProjectTemplatesResponse { synced }))
+}
+
+/// Create a new project from a template, rendering its variables with the
+/// given values.
 #[instrument(skip(state, request))]
 pub async fn create_project_from_template(
     State(state): State<AppState>,
@@ -511,68 +713,32 @@ pub async fn create_project_from_template(
         return Err(ApiError::bad_request("project path is required"));
     }
 
-    // Resolve workspace root and linux username based on shared workspace context.
+    let variables = projects::read_variables(&template_dir)
+        .map_err(|e| ApiError::internal(format!("Failed to read template variables: {e:#}")))?;
+    let values =
+        projects::resolve_values(&variables, &request.variables).map_err(ApiError::bad_request)?;
+    let staged = stage_template(&template_dir, &values)?;
+    let source_dir = staged
+        .as_ref()
+        .map(|dir| dir.path().join("template"))
+        .unwrap_or_else(|| template_dir.clone());
+
     let (workspace_root, linux_username_override) =
-        if let Some(ref sw_id) = request.shared_workspace_id {
-            // Verify user is a member of this shared workspace.
-            let sw_service = state
-                .shared_workspaces
-                .as_ref()
-                .ok_or_else(|| ApiError::bad_request("shared workspaces not available"))?;
-            let workspaces = sw_service.list_for_user(user.id()).await.map_err(|e| {
-                ApiError::internal(format!("Failed to list shared workspaces: {e}"))
-            })?;
-            let ws = workspaces.iter().find(|w| w.id == *sw_id).ok_or_else(|| {
-                ApiError::bad_request("shared workspace not found or not a member")
-            })?;
-            (PathBuf::from(&ws.path), Some(ws.linux_user.clone()))
-        } else {
-            let root = state.sessions.for_user(user.id()).workspace_root();
-            (root, None)
-        };
+        project_root(&state, user.id(), request.shared_workspace_id.as_deref()).await?;
 
     let target_dir = workspace_root.join(&project_rel);
 
-    // In multi-user mode, delegate to usermgr (runs as root, can write to user homes).
-    let is_multi_user = state.linux_users.as_ref().is_some_and(|lu| lu.enabled);
-    if is_multi_user {
-        let linux_users = state
-            .linux_users
-            .as_ref()
-            .ok_or_else(|| ApiError::internal("linux users not available"))?;
-        let linux_username = linux_username_override
-            .clone()
-            .unwrap_or_else(|| linux_users.linux_username(user.id()));
-        let target_str = target_dir
-            .to_str()
-            .ok_or_else(|| ApiError::internal("invalid target path"))?;
-
-        crate::local::linux_users::usermgr_request(
-            "create-workspace",
-            serde_json::json!({
-                "username": linux_username,
-                "path": target_str,
-                "template_src": template_dir.to_string_lossy(),
-            }),
-        )
-        .map_err(|e| ApiError::internal(format!("Failed to create project dir: {e}")))?;
-    } else {
-        // Single-user mode: create directly.
-        if !workspace_root.exists() {
-            fs::create_dir_all(&workspace_root).map_err(|e| {
-                ApiError::internal(format!(
-                    "Failed to create workspace directory {:?}: {}",
-                    workspace_root, e
-                ))
-            })?;
-        }
-
-        if target_dir.exists() {
-            return Err(ApiError::bad_request("project path already exists"));
-        }
+    create_from_template(
+        &state,
+        user.id(),
+        linux_username_override.as_deref(),
+        &workspace_root,
+        &source_dir,
+        &target_dir,
+    )?;
+    drop(staged);
 
-        copy_template_dir(&template_dir, &target_dir)?;
-    }
+    let is_multi_user = state.linux_users.as_ref().is_some_and(|lu| lu.enabled);
 
     // Initialize git repo.
     // In multi-user mode, run git as the target user via usermgr.
@@ -693,6 +859,171 @@ pub async fn create_project_from_template(
     }))
 }
 
+/// Most bytes an uploaded archive may extract to.
+const MAX_ARCHIVE_EXTRACTED_BYTES: u64 = 4 * 1024 * 1024 * 1024;
+/// Most files an uploaded archive may hold.
+const MAX_ARCHIVE_FILES: u64 = 100_000;
+
+/// Query of `POST /workspaces/from-archive`; the body is the archive.
+#[derive(Debug, Deserialize)]
+pub struct WorkspaceFromArchiveQuery {
+    /// Directory to create, relative to the workspace root.
+    pub path: String,
+    /// `zip` or `tar_gz`; detected from the content when omitted.
+    pub format: Option<ArchiveFormat>,
+    /// When set, the workspace is created inside this shared workspace.
+    pub shared_workspace_id: Option<String>,
+    /// Start a session in the new workspace.
+    #[serde(default)]
+    pub start_session: bool,
+    /// Agent of that session.
+    pub agent: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct WorkspaceFromArchiveResponse {
+    pub workspace: WorkspaceDirEntry,
+    /// Display name from `.oqto/workspace.toml`, when the archive has one.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub display_name: Option<String>,
+    pub files: u64,
+    pub bytes: u64,
+    /// Entries left out for unsafe paths or unsupported types.
+    pub skipped: u64,
+    /// Project files found at the top of the workspace.
+    pub manifests: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub session: Option<SessionWithUrls>,
+}
+
+fn archive_extract_error(err: anyhow::Error) -> ApiError {
+    match err.downcast_ref::<ErrorResponse>() {
+        Some(e) => match e.code {
+            ErrorCode::PathExists => ApiError::conflict(e.message.clone()),
+            ErrorCode::InvalidRequest | ErrorCode::PathNotAllowed => {
+                ApiError::bad_request(e.message.clone())
+            }
+            ErrorCode::PermissionDenied => ApiError::forbidden(e.message.clone()),
+            _ => ApiError::internal(format!("Archive extraction failed: {}", e.message)),
+        },
+        None => ApiError::internal(format!("Archive extraction failed: {err}")),
+    }
+}
+
+/// Create a workspace from an uploaded zip or tar.gz archive.
+///
+/// The runner of the workspace owner extracts it, so the files belong to
+/// that user. Entries escaping the directory, links and devices are
+/// skipped; an archive over the limits leaves nothing behind.
+#[instrument(skip(state, body), fields(bytes = body.len()))]
+pub async fn create_workspace_from_archive(
+    State(state): State<AppState>,
+    user: CurrentUser,
+    Query(query): Query<WorkspaceFromArchiveQuery>,
+    body: Bytes,
+) -> ApiResult<(StatusCode, Json<WorkspaceFromArchiveResponse>)> {
+    if body.is_empty() {
+        return Err(ApiError::bad_request("archive is empty"));
+    }
+    let format = query
+        .format
+        .or_else(|| ArchiveFormat::detect(&body))
+        .ok_or_else(|| ApiError::bad_request("archive must be a zip or tar.gz file"))?;
+
+    let project_rel = sanitize_relative_path(&query.path)?;
+    let is_current_dir = project_rel
+        .components()
+        .all(|c| matches!(c, std::path::Component::CurDir));
+    if is_current_dir {
+        return Err(ApiError::bad_request("workspace path is required"));
+    }
+
+    let (workspace_root, _) =
+        project_root(&state, user.id(), query.shared_workspace_id.as_deref()).await?;
+    let target = match &query.shared_workspace_id {
+        Some(workspace_id) => ExecutionTarget::SharedWorkspace {
+            workspace_id: workspace_id.clone(),
+        },
+        None => ExecutionTarget::Personal,
+    };
+    let runner = resolve_runner_for_target(&state, user.id(), &target)
+        .await
+        .map_err(|e| ApiError::internal(format!("Failed to resolve runner: {e}")))?
+        .ok_or_else(|| ApiError::service_unavailable("No runner available for this workspace"))?;
+
+    let target_dir = workspace_root.join(&project_rel);
+    let extracted = runner
+        .extract_archive(ExtractArchiveRequest {
+            path: target_dir.clone(),
+            format,
+            content_base64: base64::engine::general_purpose::STANDARD.encode(&body),
+            max_bytes: MAX_ARCHIVE_EXTRACTED_BYTES,
+            max_files: MAX_ARCHIVE_FILES,
+        })
+        .await
+        .map_err(archive_extract_error)?;
+    tracing::info!(
+        user_id = %user.id(),
+        workspace = %target_dir.display(),
+        files = extracted.files,
+        skipped = extracted.skipped,
+        manifests = ?extracted.manifests,
+        "Created workspace from archive"
+    );
+
+    if let Some(ref sw_id) = query.shared_workspace_id
+        && let Some(sw_service) = state.shared_workspaces.as_ref()
+        && let Err(e) = sw_service.regenerate_users_md_by_id(sw_id).await
+    {
+        tracing::warn!(
+            sw_id,
+            "Failed to regenerate USERS.md after workspace creation: {e}"
+        );
+    }
+
+    let session = if query.start_session {
+        let session = state
+            .sessions
+            .for_user(user.id())
+            .create_session(CreateSessionRequest {
+                workspace_path: Some(target_dir.to_string_lossy().to_string()),
+                image: None,
+                agent: query.agent,
+                env: HashMap::new(),
+                sandbox_profile: None,
+            })
+            .await?;
+        tracing::info!(session_id = %session.id, "Created session in archive workspace");
+        Some(SessionWithUrls::from_session(session, "localhost"))
+    } else {
+        None
+    };
+
+    let name = project_rel
+        .file_name()
+        .and_then(|n| n.to_str())
+        .unwrap_or("project")
+        .to_string();
+    let logo = find_project_logo(&target_dir, &name);
+    Ok((
+        StatusCode::CREATED,
+        Json(WorkspaceFromArchiveResponse {
+            display_name: crate::workspace::meta::workspace_display_name(&target_dir),
+            workspace: WorkspaceDirEntry {
+                name,
+                path: project_rel.to_string_lossy().to_string(),
+                entry_type: "directory".to_string(),
+                logo,
+            },
+            files: extracted.files,
+            bytes: extracted.bytes,
+            skipped: extracted.skipped,
+            manifests: extracted.manifests,
+            session,
+        }),
+    ))
+}
+
 // Workspace overview + Pi resource management.
 
 const GLOBAL_PI_SKILLS_DIR: &str = "~/.pi/agent/skills";
@@ -727,6 +1058,20 @@ pub struct WorkspaceSandboxUpdateRequest {
     pub profile: String,
 }
 
+#[derive(Debug, Serialize)]
+pub struct WorkspaceEncryptionResponse {
+    pub state: WorkspaceEncryptionState,
+}
+
+/// Encryption change requested for a workspace.
+#[derive(Debug, Deserialize)]
+#[serde(tag = "action", rename_all = "snake_case")]
+pub enum WorkspaceEncryptionUpdateRequest {
+    Enable { passphrase: Passphrase },
+    Unlock { passphrase: Passphrase },
+    Lock,
+}
+
 #[derive(Debug, Serialize)]
 pub struct PiResourceEntry {
     pub name: String,
@@ -916,6 +1261,87 @@ pub async fn update_workspace_sandbox(
     }))
 }
 
+fn workspace_encryption_error(err: anyhow::Error) -> ApiError {
+    match err.downcast_ref::<ErrorResponse>() {
+        Some(e) => match e.code {
+            ErrorCode::WrongPassphrase => ApiError::forbidden("Incorrect passphrase"),
+            ErrorCode::SessionAlreadyRunning | ErrorCode::WorkspaceLocked => {
+                ApiError::conflict(e.message.clone())
+            }
+            ErrorCode::InvalidRequest | ErrorCode::PathNotAllowed | ErrorCode::NotADirectory => {
+                ApiError::bad_request(e.message.clone())
+            }
+            _ => ApiError::internal(format!("Workspace encryption failed: {}", e.message)),
+        },
+        None => ApiError::internal(format!("Workspace encryption failed: {err}")),
+    }
+}
+
+#[instrument(skip(state, user, query))]
+pub async fn get_workspace_encryption(
+    State(state): State<AppState>,
+    user: CurrentUser,
+    Query(query): Query<WorkspaceMetaQuery>,
+) -> ApiResult<Json<WorkspaceEncryptionResponse>> {
+    if !state.workspace_encryption.enabled {
+        return Ok(Json(WorkspaceEncryptionResponse {
+            state: WorkspaceEncryptionState::Disabled,
+        }));
+    }
+    let (workspace_root, runner) =
+        validated_runner(&state, user.id(), &query.workspace_path).await?;
+
+    let resp = runner
+        .workspace_encryption(workspace_root, WorkspaceEncryptionAction::Status)
+        .await
+        .map_err(workspace_encryption_error)?;
+    Ok(Json(WorkspaceEncryptionResponse { state: resp.state }))
+}
+
+/// Enable, unlock or lock at-rest encryption for a workspace.
+///
+/// Sessions in a locked workspace fail to start until it is unlocked; the
+/// runner locks it again once the last session in it has stopped.
+#[instrument(skip(state, user, query, request))]
+pub async fn update_workspace_encryption(
+    State(state): State<AppState>,
+    user: CurrentUser,
+    Query(query): Query<WorkspaceMetaQuery>,
+    Json(request): Json<WorkspaceEncryptionUpdateRequest>,
+) -> ApiResult<Json<WorkspaceEncryptionResponse>> {
+    if !state.workspace_encryption.enabled {
+        return Err(ApiError::service_unavailable(
+            "Workspace encryption is disabled",
+        ));
+    }
+    let action = match request {
+        WorkspaceEncryptionUpdateRequest::Enable { passphrase } => {
+            let min = state.workspace_encryption.min_passphrase_length;
+            if passphrase.len() < min {
+                return Err(ApiError::bad_request(format!(
+                    "Passphrase must be at least {min} characters"
+                )));
+            }
+            WorkspaceEncryptionAction::Enable { passphrase }
+        }
+        WorkspaceEncryptionUpdateRequest::Unlock { passphrase } => {
+            if passphrase.is_empty() {
+                return Err(ApiError::bad_request("Passphrase is required"));
+            }
+            WorkspaceEncryptionAction::Unlock { passphrase }
+        }
+        WorkspaceEncryptionUpdateRequest::Lock => WorkspaceEncryptionAction::Lock,
+    };
+    let (workspace_root, runner) =
+        validated_runner(&state, user.id(), &query.workspace_path).await?;
+
+    let resp = runner
+        .workspace_encryption(workspace_root, action)
+        .await
+        .map_err(workspace_encryption_error)?;
+    Ok(Json(WorkspaceEncryptionResponse { state: resp.state }))
+}
+
 #[instrument(skip(state, user, query))]
 pub async fn get_workspace_pi_resources(
     State(state): State<AppState>,
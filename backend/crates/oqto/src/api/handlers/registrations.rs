@@ -0,0 +1,98 @@
+//! Open registration handlers: email verification and the approval queue
+//! (admin or `invites.manage`).
+
+use std::sync::Arc;
+
+use axum::{
+    Json,
+    extract::{Path, Query, State},
+    http::StatusCode,
+    response::Redirect,
+};
+use serde::Deserialize;
+use tracing::{info, instrument};
+
+use crate::auth::{Access, CurrentUser, Permission};
+use crate::registration::{
+    Registration, RegistrationListQuery, RegistrationService, RegistrationStatus,
+};
+
+use crate::api::error::{ApiError, ApiResult};
+use crate::api::state::AppState;
+
+fn registration(state: &AppState) -> ApiResult<&Arc<RegistrationService>> {
+    state
+        .registration
+        .as_ref()
+        .ok_or_else(|| ApiError::not_found("Open registration is disabled"))
+}
+
+/// Query of the link in verification emails.
+#[derive(Debug, Deserialize)]
+pub struct VerifyEmailQuery {
+    pub token: String,
+}
+
+/// Verify an email address from the emailed link and send the browser on
+/// to the app.
+#[instrument(skip(state, query))]
+pub async fn verify_email(
+    State(state): State<AppState>,
+    Query(query): Query<VerifyEmailQuery>,
+) -> ApiResult<Redirect> {
+    registration(&state)?.verify(&query.token).await?;
+    Ok(Redirect::to("/?email_verified=1"))
+}
+
+/// Send the current user a new verification link.
+#[instrument(skip(state, user), fields(user_id = %user.id()))]
+pub async fn resend_verification_email(
+    State(state): State<AppState>,
+    user: CurrentUser,
+) -> ApiResult<StatusCode> {
+    registration(&state)?.resend_verification(user.id()).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// List open registrations, by default those waiting for approval.
+#[instrument(skip(state, access))]
+pub async fn list_registrations(
+    State(state): State<AppState>,
+    access: Access,
+    Query(query): Query<RegistrationListQuery>,
+) -> ApiResult<Json<Vec<Registration>>> {
+    access.require(Permission::InvitesManage)?;
+    let status = query.status.unwrap_or(RegistrationStatus::PendingApproval);
+    Ok(Json(registration(&state)?.list(status).await?))
+}
+
+/// Approve a registration and enable the account.
+#[instrument(skip(state, access))]
+pub async fn approve_registration(
+    State(state): State<AppState>,
+    access: Access,
+    Path(user_id): Path<String>,
+) -> ApiResult<Json<Registration>> {
+    let reviewer = access.require(Permission::InvitesManage)?;
+    let registration = registration(&state)?
+        .approve(&user_id, reviewer.id())
+        .await?;
+    state.users.activate_user(&user_id).await?;
+    info!(user_id = %user_id, "Approved open registration");
+    Ok(Json(registration))
+}
+
+/// Reject a registration waiting for approval. The account stays disabled.
+#[instrument(skip(state, access))]
+pub async fn reject_registration(
+    State(state): State<AppState>,
+    access: Access,
+    Path(user_id): Path<String>,
+) -> ApiResult<Json<Registration>> {
+    let reviewer = access.require(Permission::InvitesManage)?;
+    let registration = registration(&state)?
+        .reject(&user_id, reviewer.id())
+        .await?;
+    info!(user_id = %user_id, "Rejected open registration");
+    Ok(Json(registration))
+}
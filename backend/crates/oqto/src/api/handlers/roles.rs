@@ -0,0 +1,180 @@
+//! Role management handlers (admin only) and the caller's own permissions.
+
+use std::sync::Arc;
+
+use axum::{
+    Json,
+    extract::{Path, State},
+    http::StatusCode,
+};
+use serde::{Deserialize, Serialize};
+use tracing::{info, instrument};
+
+use crate::auth::{
+    Access, CreateRoleRequest, Permission, RbacService, RequireAdmin, RoleInfo, UpdateRoleRequest,
+    valid_role_name,
+};
+
+use crate::api::error::{ApiError, ApiResult};
+use crate::api::state::AppState;
+
+/// Roles assigned to a user.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct UserRolesBody {
+    pub roles: Vec<String>,
+}
+
+/// The caller's roles and what they allow.
+#[derive(Debug, Serialize)]
+pub struct MyPermissionsResponse {
+    pub admin: bool,
+    pub roles: Vec<String>,
+    pub permissions: Vec<Permission>,
+}
+
+fn rbac(state: &AppState) -> ApiResult<&Arc<RbacService>> {
+    state
+        .rbac
+        .as_ref()
+        .ok_or_else(|| ApiError::service_unavailable("Role-based access control is not enabled"))
+}
+
+/// List all roles (admin only).
+#[instrument(skip(state, _user))]
+pub async fn list_roles(
+    State(state): State<AppState>,
+    RequireAdmin(_user): RequireAdmin,
+) -> ApiResult<Json<Vec<RoleInfo>>> {
+    Ok(Json(rbac(&state)?.repo().list_roles().await?))
+}
+
+/// Create a role (admin only).
+#[instrument(skip(state, _user, request), fields(name = %request.name))]
+pub async fn create_role(
+    State(state): State<AppState>,
+    RequireAdmin(_user): RequireAdmin,
+    Json(request): Json<CreateRoleRequest>,
+) -> ApiResult<(StatusCode, Json<RoleInfo>)> {
+    let rbac = rbac(&state)?;
+    if !valid_role_name(&request.name) {
+        return Err(ApiError::bad_request(
+            "Role names may only contain lowercase letters, digits, '-' and '_'",
+        ));
+    }
+    if rbac.repo().get_role(&request.name).await?.is_some() {
+        return Err(ApiError::conflict(format!(
+            "Role {} already exists",
+            request.name
+        )));
+    }
+    rbac.repo().create_role(&request).await?;
+    let role = rbac
+        .repo()
+        .get_role(&request.name)
+        .await?
+        .ok_or_else(|| ApiError::internal("Created role not found"))?;
+    info!(role = %role.name, "Created role");
+    Ok((StatusCode::CREATED, Json(role)))
+}
+
+/// Replace the description and permissions of a role (admin only).
+#[instrument(skip(state, _user, request))]
+pub async fn update_role(
+    State(state): State<AppState>,
+    RequireAdmin(_user): RequireAdmin,
+    Path(name): Path<String>,
+    Json(request): Json<UpdateRoleRequest>,
+) -> ApiResult<Json<RoleInfo>> {
+    let rbac = rbac(&state)?;
+    if !rbac.repo().update_role(&name, &request).await? {
+        return Err(ApiError::not_found(format!("Role {} not found", name)));
+    }
+    rbac.invalidate(None);
+    let role = rbac
+        .repo()
+        .get_role(&name)
+        .await?
+        .ok_or_else(|| ApiError::not_found(format!("Role {} not found", name)))?;
+    info!(role = %name, "Updated role");
+    Ok(Json(role))
+}
+
+/// Delete a role that is not built in (admin only).
+#[instrument(skip(state, _user))]
+pub async fn delete_role(
+    State(state): State<AppState>,
+    RequireAdmin(_user): RequireAdmin,
+    Path(name): Path<String>,
+) -> ApiResult<StatusCode> {
+    let rbac = rbac(&state)?;
+    match rbac.repo().get_role(&name).await? {
+        None => return Err(ApiError::not_found(format!("Role {} not found", name))),
+        Some(role) if role.builtin => {
+            return Err(ApiError::bad_request(format!(
+                "Role {} is built in and cannot be deleted",
+                name
+            )));
+        }
+        Some(_) => {}
+    }
+    rbac.repo().delete_role(&name).await?;
+    rbac.invalidate(None);
+    info!(role = %name, "Deleted role");
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Roles of a user (admin only).
+#[instrument(skip(state, _user))]
+pub async fn get_user_roles(
+    State(state): State<AppState>,
+    RequireAdmin(_user): RequireAdmin,
+    Path(user_id): Path<String>,
+) -> ApiResult<Json<UserRolesBody>> {
+    let roles = rbac(&state)?.repo().user_roles(&user_id).await?;
+    Ok(Json(UserRolesBody { roles }))
+}
+
+/// Replace the roles of a user (admin only).
+#[instrument(skip(state, user, request))]
+pub async fn set_user_roles(
+    State(state): State<AppState>,
+    RequireAdmin(user): RequireAdmin,
+    Path(user_id): Path<String>,
+    Json(request): Json<UserRolesBody>,
+) -> ApiResult<Json<UserRolesBody>> {
+    let rbac = rbac(&state)?;
+    if state.users.get_user(&user_id).await?.is_none() {
+        return Err(ApiError::not_found(format!("User {} not found", user_id)));
+    }
+    let mut roles = request.roles;
+    roles.sort();
+    roles.dedup();
+    for role in &roles {
+        if rbac.repo().get_role(role).await?.is_none() {
+            return Err(ApiError::bad_request(format!("Unknown role: {}", role)));
+        }
+    }
+    rbac.repo()
+        .set_user_roles(&user_id, &roles, user.id())
+        .await?;
+    rbac.invalidate(Some(&user_id));
+    info!(user_id = %user_id, roles = ?roles, "Set user roles");
+    Ok(Json(UserRolesBody { roles }))
+}
+
+/// Roles and permissions of the current user.
+#[instrument(skip(state, access))]
+pub async fn get_my_permissions(
+    State(state): State<AppState>,
+    access: Access,
+) -> ApiResult<Json<MyPermissionsResponse>> {
+    let roles = match state.rbac.as_ref() {
+        Some(rbac) => rbac.repo().user_roles(access.user.id()).await?,
+        None => Vec::new(),
+    };
+    Ok(Json(MyPermissionsResponse {
+        admin: access.user.is_admin(),
+        roles,
+        permissions: access.permissions.list(),
+    }))
+}
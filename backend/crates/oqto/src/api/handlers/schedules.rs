@@ -0,0 +1,541 @@
+//! Agent schedule handlers and the loop that runs them.
+//!
+//! An agent schedule starts a new session in a workspace on a cron schedule,
+//! sends it a prompt (or runs a prompt template) and closes the session once
+//! the agent is idle again, e.g. nightly dependency updates. Runs are
+//! recorded in the schedule run history under the schedule's name
+//! (`GET /api/schedules/{name}/runs`).
+
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::{Context, bail};
+use axum::{
+    Json,
+    extract::{Path, State},
+    http::StatusCode,
+};
+use chrono::{DateTime, Utc};
+use tracing::{info, instrument, warn};
+
+use oqto_runner::client::RunnerClient;
+use oqto_runner::protocol::{PiCreateSessionRequest, PiSessionConfig as RunnerPiSessionConfig};
+
+use crate::auth::CurrentUser;
+use crate::macros::{MacroStep, RunnerAgent, StepStatus, run_steps};
+use crate::runner::router::{
+    ExecutionTarget, resolve_runner_for_session, resolve_target_for_workspace_path,
+};
+use crate::scheduler::{
+    AgentSchedule, SaveAgentScheduleRequest, ScheduleRunRecord, ScheduleRunReport,
+    ScheduleRunStatus, SchedulerService, next_cron_run,
+};
+use crate::session_target::{SessionTargetRecord, SessionTargetScope};
+use crate::shared_workspace::SharePermission;
+
+use super::trx::validate_workspace_path;
+use crate::api::error::{ApiError, ApiResult};
+use crate::api::state::AppState;
+
+/// Longest accepted schedule or trigger name.
+const MAX_NAME_LEN: usize = 64;
+
+fn scheduler(state: &AppState) -> ApiResult<&Arc<SchedulerService>> {
+    state
+        .scheduler
+        .as_ref()
+        .ok_or_else(|| ApiError::service_unavailable("Scheduler is disabled"))
+}
+
+async fn owned_schedule(
+    scheduler: &SchedulerService,
+    user_id: &str,
+    name: &str,
+) -> ApiResult<AgentSchedule> {
+    scheduler
+        .agent_schedules()
+        .get(user_id, name)
+        .await?
+        .ok_or_else(|| ApiError::not_found(format!("Schedule '{name}' not found")))
+}
+
+/// Names of schedules and triggers appear in URLs.
+pub(super) fn validate_name(name: &str) -> ApiResult<()> {
+    if name.is_empty()
+        || name.len() > MAX_NAME_LEN
+        || !name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+    {
+        return Err(ApiError::bad_request(format!(
+            "name must be 1-{MAX_NAME_LEN} letters, digits, '-' or '_'"
+        )));
+    }
+    Ok(())
+}
+
+/// Resolve a workspace path runs may use: the user's own, or a shared
+/// workspace they can chat in.
+pub(super) async fn checked_workspace_path(
+    state: &AppState,
+    user_id: &str,
+    workspace_path: &str,
+) -> ApiResult<String> {
+    let target = resolve_target_for_workspace_path(state, user_id, workspace_path)
+        .await
+        .map_err(|e| ApiError::internal(format!("Failed to resolve workspace target: {e}")))?;
+    if let ExecutionTarget::SharedWorkspace { workspace_id } = &target {
+        let shared = state
+            .shared_workspaces
+            .as_ref()
+            .ok_or_else(|| ApiError::not_found("Shared workspaces are not enabled"))?;
+        if !shared
+            .permissions_for_workspace(workspace_id, user_id)
+            .await?
+            .allows(SharePermission::ChatWrite)
+        {
+            return Err(ApiError::forbidden(
+                "Missing 'chat_write' permission in this shared workspace",
+            ));
+        }
+    }
+    let workspace = validate_workspace_path(state, user_id, workspace_path).await?;
+    Ok(workspace.to_string_lossy().to_string())
+}
+
+/// Validate a definition, resolve its workspace path and compute its first
+/// run (None while disabled).
+async fn checked(
+    state: &AppState,
+    user_id: &str,
+    name: &str,
+    mut request: SaveAgentScheduleRequest,
+) -> ApiResult<(SaveAgentScheduleRequest, Option<DateTime<Utc>>)> {
+    validate_name(name)?;
+    request.cron = request.cron.trim().to_string();
+    let next_run_at = next_cron_run(&request.cron, Utc::now())
+        .map_err(|e| ApiError::bad_request(e.to_string()))?;
+    if request.prompt.trim().is_empty() && request.template.is_none() {
+        return Err(ApiError::bad_request("prompt must not be empty"));
+    }
+    request.template = request
+        .template
+        .as_deref()
+        .map(|t| t.trim().trim_start_matches('/').to_string());
+    if request.template.as_deref() == Some("") {
+        return Err(ApiError::bad_request("template must not be empty"));
+    }
+    if request.timeout_secs.is_some_and(|t| t < 1) {
+        return Err(ApiError::bad_request("timeout_secs must be at least 1"));
+    }
+
+    request.workspace_path =
+        checked_workspace_path(state, user_id, &request.workspace_path).await?;
+
+    let next_run_at = request.enabled.then_some(next_run_at);
+    Ok((request, next_run_at))
+}
+
+/// The caller's agent schedules.
+#[instrument(skip(state, user))]
+pub async fn list_agent_schedules(
+    State(state): State<AppState>,
+    user: CurrentUser,
+) -> ApiResult<Json<Vec<AgentSchedule>>> {
+    Ok(Json(
+        scheduler(&state)?.agent_schedules().list(user.id()).await?,
+    ))
+}
+
+/// Define an agent schedule.
+#[instrument(skip(state, user, request))]
+pub async fn create_agent_schedule(
+    State(state): State<AppState>,
+    user: CurrentUser,
+    Json(request): Json<SaveAgentScheduleRequest>,
+) -> ApiResult<(StatusCode, Json<AgentSchedule>)> {
+    let scheduler = scheduler(&state)?;
+    let name = request
+        .name
+        .as_deref()
+        .unwrap_or_default()
+        .trim()
+        .to_string();
+    let (request, next_run_at) = checked(&state, user.id(), &name, request).await?;
+    let repo = scheduler.agent_schedules();
+    if repo.get(user.id(), &name).await?.is_some() {
+        return Err(ApiError::conflict(format!(
+            "A schedule named '{name}' already exists"
+        )));
+    }
+    if repo.count(user.id()).await? >= scheduler.config().max_agent_schedules_per_user {
+        return Err(ApiError::too_many_requests("Too many schedules"));
+    }
+
+    let created = repo
+        .upsert(user.id(), &name, &request, next_run_at, Utc::now())
+        .await?;
+    info!(user_id = %user.id(), schedule = %name, cron = %created.cron, "agent schedule created");
+    Ok((StatusCode::CREATED, Json(created)))
+}
+
+#[instrument(skip(state, user))]
+pub async fn get_agent_schedule(
+    State(state): State<AppState>,
+    user: CurrentUser,
+    Path(name): Path<String>,
+) -> ApiResult<Json<AgentSchedule>> {
+    Ok(Json(
+        owned_schedule(scheduler(&state)?, user.id(), &name).await?,
+    ))
+}
+
+/// Replace an agent schedule's definition. Its next run is recomputed.
+#[instrument(skip(state, user, request))]
+pub async fn update_agent_schedule(
+    State(state): State<AppState>,
+    user: CurrentUser,
+    Path(name): Path<String>,
+    Json(request): Json<SaveAgentScheduleRequest>,
+) -> ApiResult<Json<AgentSchedule>> {
+    let scheduler = scheduler(&state)?;
+    owned_schedule(scheduler, user.id(), &name).await?;
+    let (request, next_run_at) = checked(&state, user.id(), &name, request).await?;
+    let updated = scheduler
+        .agent_schedules()
+        .upsert(user.id(), &name, &request, next_run_at, Utc::now())
+        .await?;
+    Ok(Json(updated))
+}
+
+/// Delete an agent schedule. A run in progress finishes; the run history
+/// is kept.
+#[instrument(skip(state, user))]
+pub async fn delete_agent_schedule(
+    State(state): State<AppState>,
+    user: CurrentUser,
+    Path(name): Path<String>,
+) -> ApiResult<StatusCode> {
+    if !scheduler(&state)?
+        .agent_schedules()
+        .delete(user.id(), &name)
+        .await?
+    {
+        return Err(ApiError::not_found(format!("Schedule '{name}' not found")));
+    }
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Run an agent schedule now, outside its cron schedule. Responds with the
+/// started run; its result is recorded in the run history.
+#[instrument(skip(state, user))]
+pub async fn run_agent_schedule(
+    State(state): State<AppState>,
+    user: CurrentUser,
+    Path(name): Path<String>,
+) -> ApiResult<(StatusCode, Json<ScheduleRunRecord>)> {
+    let scheduler = scheduler(&state)?;
+    let schedule = owned_schedule(scheduler, user.id(), &name).await?;
+    let record = start_agent_run(&state, scheduler, schedule, None)
+        .await?
+        .ok_or_else(|| ApiError::conflict(format!("Schedule '{name}' is already running")))?;
+    Ok((StatusCode::ACCEPTED, Json(record)))
+}
+
+/// Start due agent schedules every `agent_poll_interval_seconds`.
+pub async fn run_agent_schedules(state: AppState) {
+    let Some(scheduler) = state.scheduler.clone() else {
+        return;
+    };
+    let mut interval = tokio::time::interval(Duration::from_secs(
+        scheduler.config().agent_poll_interval_seconds.max(1),
+    ));
+    loop {
+        interval.tick().await;
+        let due = match scheduler.take_due_agent_runs(Utc::now()).await {
+            Ok(due) => due,
+            Err(e) => {
+                warn!("Failed to look up due agent schedules: {e:#}");
+                continue;
+            }
+        };
+        for run in due {
+            let name = run.schedule.name.clone();
+            let user_id = run.schedule.user_id.clone();
+            match start_agent_run(&state, &scheduler, run.schedule, Some(run.scheduled_for)).await {
+                Ok(Some(_)) => {}
+                Ok(None) => {
+                    warn!(user_id = %user_id, schedule = %name, "Previous run still in progress, skipping");
+                    let mut report = run_report(None, ScheduleRunStatus::Skipped);
+                    report.scheduled_for = Some(run.scheduled_for.to_rfc3339());
+                    report.error = Some("Previous run was still in progress".to_string());
+                    if let Err(e) = scheduler.record_run(&user_id, &name, &report).await {
+                        warn!("Failed to record skipped agent run: {e:#}");
+                    }
+                }
+                Err(e) => {
+                    warn!(user_id = %user_id, schedule = %name, "Failed to start agent run: {e:#}")
+                }
+            }
+        }
+    }
+}
+
+fn run_report(id: Option<String>, status: ScheduleRunStatus) -> ScheduleRunReport {
+    ScheduleRunReport {
+        id,
+        status,
+        scheduled_for: None,
+        started_at: None,
+        ended_at: None,
+        exit_code: None,
+        log_path: None,
+        cost_usd: None,
+        error: None,
+        session_id: None,
+    }
+}
+
+/// Record a run of `schedule` and start it in the background. None while
+/// the schedule's previous run is still in progress.
+async fn start_agent_run(
+    state: &AppState,
+    scheduler: &Arc<SchedulerService>,
+    schedule: AgentSchedule,
+    scheduled_for: Option<DateTime<Utc>>,
+) -> anyhow::Result<Option<ScheduleRunRecord>> {
+    if !scheduler.begin_agent_run(&schedule.id) {
+        return Ok(None);
+    }
+    let session_id = uuid::Uuid::new_v4().to_string();
+    let started_at = Utc::now();
+    let mut report = run_report(None, ScheduleRunStatus::Running);
+    report.scheduled_for = scheduled_for.map(|t| t.to_rfc3339());
+    report.started_at = Some(started_at.to_rfc3339());
+    report.session_id = Some(session_id.clone());
+    let record = match scheduler
+        .record_run(&schedule.user_id, &schedule.name, &report)
+        .await
+    {
+        Ok(Some(record)) => record,
+        Ok(None) => {
+            scheduler.end_agent_run(&schedule.id);
+            bail!("run ID collision");
+        }
+        Err(e) => {
+            scheduler.end_agent_run(&schedule.id);
+            return Err(e);
+        }
+    };
+    if let Err(e) = scheduler
+        .agent_schedules()
+        .mark_run(&schedule.id, started_at)
+        .await
+    {
+        warn!("Failed to record last agent schedule run: {e:#}");
+    }
+
+    let state = state.clone();
+    let scheduler = Arc::clone(scheduler);
+    let run_id = record.id.clone();
+    tokio::spawn(async move {
+        let _permit = match &state.priority_lanes {
+            Some(lanes) => lanes.acquire_background(&schedule.name).await,
+            None => None,
+        };
+        info!(
+            user_id = %schedule.user_id,
+            schedule = %schedule.name,
+            session_id = %session_id,
+            "Starting agent schedule run"
+        );
+        let spec = AgentRunSpec::for_schedule(&schedule, scheduler.config().agent_run_timeout_secs);
+        let result = execute_agent_run(&state, &spec, &session_id).await;
+        scheduler.end_agent_run(&schedule.id);
+        if let Err(e) = &result {
+            warn!(
+                user_id = %schedule.user_id,
+                schedule = %schedule.name,
+                "Agent schedule run failed: {e:#}"
+            );
+        }
+
+        let mut report = run_report(
+            Some(run_id),
+            if result.is_ok() {
+                ScheduleRunStatus::Succeeded
+            } else {
+                ScheduleRunStatus::Failed
+            },
+        );
+        report.ended_at = Some(Utc::now().to_rfc3339());
+        report.error = result.err().map(|e| format!("{e:#}"));
+        if let Err(e) = scheduler
+            .record_run(&schedule.user_id, &schedule.name, &report)
+            .await
+        {
+            warn!("Failed to record agent schedule run: {e:#}");
+        }
+    });
+    Ok(Some(record))
+}
+
+/// What an agent run starts and sends.
+pub(super) struct AgentRunSpec<'a> {
+    pub user_id: &'a str,
+    pub workspace_path: &'a str,
+    /// Sent as the first message, or as the arguments of `template`.
+    pub prompt: &'a str,
+    pub template: Option<&'a str>,
+    pub harness: Option<&'a str>,
+    pub provider: Option<&'a str>,
+    pub model: Option<&'a str>,
+    pub timeout: Duration,
+}
+
+impl<'a> AgentRunSpec<'a> {
+    fn for_schedule(schedule: &'a AgentSchedule, default_timeout_secs: u64) -> Self {
+        Self {
+            user_id: &schedule.user_id,
+            workspace_path: &schedule.workspace_path,
+            prompt: &schedule.prompt,
+            template: schedule.template.as_deref(),
+            harness: schedule.harness.as_deref(),
+            provider: schedule.provider.as_deref(),
+            model: schedule.model.as_deref(),
+            timeout: Duration::from_secs(
+                schedule
+                    .timeout_secs
+                    .map_or(default_timeout_secs, |t| t.max(1) as u64),
+            ),
+        }
+    }
+}
+
+/// Start an agent session in a workspace on the runner that serves it and
+/// record where it runs.
+pub(super) async fn open_agent_session(
+    state: &AppState,
+    user_id: &str,
+    workspace_path: &str,
+    session_id: &str,
+    config: RunnerPiSessionConfig,
+) -> anyhow::Result<RunnerClient> {
+    let target = resolve_target_for_workspace_path(state, user_id, workspace_path)
+        .await
+        .context("resolving workspace target")?;
+    let runner = resolve_runner_for_session(state, user_id, session_id, &target, true)
+        .await
+        .context("resolving runner")?
+        .context("no runner available for workspace")?;
+
+    let record = match &target {
+        ExecutionTarget::Personal => SessionTargetRecord {
+            session_id: session_id.to_string(),
+            owner_user_id: Some(user_id.to_string()),
+            scope: SessionTargetScope::Personal,
+            workspace_id: None,
+            workspace_path: Some(workspace_path.to_string()),
+        },
+        ExecutionTarget::SharedWorkspace { workspace_id } => SessionTargetRecord {
+            session_id: session_id.to_string(),
+            owner_user_id: None,
+            scope: SessionTargetScope::SharedWorkspace,
+            workspace_id: Some(workspace_id.clone()),
+            workspace_path: Some(workspace_path.to_string()),
+        },
+    };
+    state
+        .session_targets
+        .upsert(&record)
+        .await
+        .context("recording session target")?;
+
+    runner
+        .agent_create_session(PiCreateSessionRequest {
+            session_id: session_id.to_string(),
+            config,
+        })
+        .await
+        .context("starting session")?;
+    if let Some(webhooks) = &state.webhooks {
+        webhooks
+            .watch_runs(
+                &runner,
+                session_id,
+                serde_json::json!({
+                    "user_id": user_id,
+                    "workdir": workspace_path,
+                }),
+            )
+            .await;
+    }
+    Ok(runner)
+}
+
+/// Start the run's session, send the prompt, wait until the agent is idle
+/// and close the session again. Returns the agent's last answer.
+pub(super) async fn execute_agent_run(
+    state: &AppState,
+    spec: &AgentRunSpec<'_>,
+    session_id: &str,
+) -> anyhow::Result<Option<String>> {
+    let runner = open_agent_session(
+        state,
+        spec.user_id,
+        spec.workspace_path,
+        session_id,
+        RunnerPiSessionConfig {
+            cwd: PathBuf::from(spec.workspace_path),
+            provider: spec.provider.map(str::to_string),
+            model: spec.model.map(str::to_string),
+            session_file: None,
+            continue_session: None,
+            env: HashMap::new(),
+            harness: spec.harness.map(str::to_string),
+            sandbox_profile: None,
+        },
+    )
+    .await?;
+
+    let step = match spec.template {
+        Some(name) => MacroStep::Template {
+            name: name.to_string(),
+            args: spec.prompt.to_string(),
+            wait: true,
+        },
+        None => MacroStep::Prompt {
+            message: spec.prompt.to_string(),
+            wait: true,
+        },
+    };
+    let result = match RunnerAgent::connect(runner.clone(), session_id).await {
+        Ok(mut agent) => {
+            let results = run_steps(&mut agent, &[step], spec.timeout).await;
+            match results.into_iter().next() {
+                Some(result) if result.status == StepStatus::Ok => Ok(()),
+                Some(result) => Err(anyhow::anyhow!(
+                    result.error.unwrap_or_else(|| "prompt failed".to_string())
+                )),
+                None => Err(anyhow::anyhow!("prompt was not sent")),
+            }
+        }
+        Err(e) => Err(e.context("subscribing to session events")),
+    };
+    let answer = match &result {
+        Ok(()) => match runner.pi_get_last_assistant_text(session_id).await {
+            Ok(response) => response.text,
+            Err(e) => {
+                warn!(session_id = %session_id, "Failed to read the agent's answer: {e:#}");
+                None
+            }
+        },
+        Err(_) => None,
+    };
+    if let Err(e) = runner.agent_close_session(session_id).await {
+        warn!(session_id = %session_id, "Failed to close scheduled session: {e:#}");
+    }
+    result.map(|()| answer)
+}
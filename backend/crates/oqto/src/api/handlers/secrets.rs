@@ -0,0 +1,123 @@
+//! Agent secrets handlers.
+//!
+//! Values are write-only: they are sealed on the way in and only ever
+//! opened to inject them into the caller's sessions.
+
+use axum::{
+    Json,
+    extract::{Path, State},
+    http::StatusCode,
+};
+use tracing::{info, instrument};
+
+use crate::auth::CurrentUser;
+use crate::secrets::{
+    CreateSecretRequest, MAX_SECRET_BYTES, SecretInfo, SecretStore, UpdateSecretRequest,
+    validate_name,
+};
+
+use super::trx::validate_workspace_path;
+use crate::api::error::{ApiError, ApiResult};
+use crate::api::state::AppState;
+
+fn secret_store(state: &AppState) -> ApiResult<&SecretStore> {
+    state
+        .secrets
+        .as_deref()
+        .ok_or_else(|| ApiError::not_found("Secrets are not enabled"))
+}
+
+fn check_value(value: &str) -> ApiResult<()> {
+    if value.is_empty() {
+        return Err(ApiError::bad_request("value must not be empty"));
+    }
+    if value.len() > MAX_SECRET_BYTES {
+        return Err(ApiError::bad_request(format!(
+            "value is larger than {MAX_SECRET_BYTES} bytes"
+        )));
+    }
+    Ok(())
+}
+
+/// The caller's secrets (values are never returned).
+#[instrument(skip(state, user))]
+pub async fn list_secrets(
+    State(state): State<AppState>,
+    user: CurrentUser,
+) -> ApiResult<Json<Vec<SecretInfo>>> {
+    Ok(Json(secret_store(&state)?.list(user.id()).await?))
+}
+
+#[instrument(skip(state, user))]
+pub async fn get_secret(
+    State(state): State<AppState>,
+    user: CurrentUser,
+    Path(id): Path<String>,
+) -> ApiResult<Json<SecretInfo>> {
+    secret_store(&state)?
+        .get(user.id(), &id)
+        .await?
+        .map(Json)
+        .ok_or_else(|| ApiError::not_found("Secret not found"))
+}
+
+/// Store a secret for all of the caller's sessions or for one project.
+#[instrument(skip(state, user, request))]
+pub async fn create_secret(
+    State(state): State<AppState>,
+    user: CurrentUser,
+    Json(request): Json<CreateSecretRequest>,
+) -> ApiResult<(StatusCode, Json<SecretInfo>)> {
+    let store = secret_store(&state)?;
+    validate_name(request.kind, &request.name).map_err(ApiError::bad_request)?;
+    check_value(&request.value)?;
+    let workspace_path = match request.workspace_path.as_deref() {
+        Some(path) => Some(
+            validate_workspace_path(&state, user.id(), path)
+                .await?
+                .to_string_lossy()
+                .to_string(),
+        ),
+        None => None,
+    };
+    let secret = store
+        .create(
+            user.id(),
+            workspace_path.as_deref(),
+            request.kind,
+            &request.name,
+            &request.value,
+        )
+        .await?;
+    info!(user_id = %user.id(), secret_id = %secret.id, "secret created");
+    Ok((StatusCode::CREATED, Json(secret)))
+}
+
+/// Replace the value of a secret; new sessions get the new value.
+#[instrument(skip(state, user, request))]
+pub async fn update_secret(
+    State(state): State<AppState>,
+    user: CurrentUser,
+    Path(id): Path<String>,
+    Json(request): Json<UpdateSecretRequest>,
+) -> ApiResult<StatusCode> {
+    let store = secret_store(&state)?;
+    check_value(&request.value)?;
+    if !store.update(user.id(), &id, &request.value).await? {
+        return Err(ApiError::not_found("Secret not found"));
+    }
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[instrument(skip(state, user))]
+pub async fn delete_secret(
+    State(state): State<AppState>,
+    user: CurrentUser,
+    Path(id): Path<String>,
+) -> ApiResult<StatusCode> {
+    if !secret_store(&state)?.delete(user.id(), &id).await? {
+        return Err(ApiError::not_found("Secret not found"));
+    }
+    info!(user_id = %user.id(), secret_id = %id, "secret deleted");
+    Ok(StatusCode::NO_CONTENT)
+}
@@ -0,0 +1,472 @@
+//! Session draft handlers: configure a session now, start it later.
+
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use anyhow::Context;
+use axum::{
+    Json,
+    extract::{Path, State},
+    http::StatusCode,
+};
+use chrono::Utc;
+use tracing::{info, instrument, warn};
+
+use oqto_runner::protocol::PiSessionConfig as RunnerPiSessionConfig;
+
+use crate::auth::CurrentUser;
+use crate::session::{CreateSessionRequest, SessionSetup, validate_setup_env};
+use crate::session_drafts::{
+    DraftShare, SaveSessionDraftRequest, SessionDraft, SessionDraftRepository,
+    ShareSessionDraftRequest,
+};
+use crate::ws::types::WsEvent;
+
+use super::schedules::{checked_workspace_path, open_agent_session};
+use crate::api::error::{ApiError, ApiResult};
+use crate::api::state::AppState;
+
+/// How often drafts with a `start_at` are checked.
+const DRAFT_POLL_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Longest draft name.
+const MAX_DRAFT_NAME_LEN: usize = 200;
+
+fn drafts(state: &AppState) -> ApiResult<&SessionDraftRepository> {
+    state
+        .session_drafts
+        .as_deref()
+        .ok_or_else(|| ApiError::not_found("Session drafts are not available"))
+}
+
+/// The caller's draft.
+async fn owned_draft(
+    repo: &SessionDraftRepository,
+    user_id: &str,
+    draft_id: &str,
+) -> ApiResult<SessionDraft> {
+    repo.get(draft_id)
+        .await?
+        .filter(|draft| draft.user_id == user_id)
+        .ok_or_else(|| ApiError::not_found(format!("Session draft {draft_id} not found")))
+}
+
+/// Normalize and validate a draft before it is saved.
+async fn checked(
+    state: &AppState,
+    user_id: &str,
+    mut request: SaveSessionDraftRequest,
+) -> ApiResult<SaveSessionDraftRequest> {
+    fn trimmed(value: Option<String>) -> Option<String> {
+        value
+            .map(|v| v.trim().to_string())
+            .filter(|v| !v.is_empty())
+    }
+    request.name = trimmed(request.name);
+    if request
+        .name
+        .as_ref()
+        .is_some_and(|name| name.chars().count() > MAX_DRAFT_NAME_LEN)
+    {
+        return Err(ApiError::bad_request(format!(
+            "name must be at most {MAX_DRAFT_NAME_LEN} characters"
+        )));
+    }
+    request.image = trimmed(request.image);
+    request.agent = trimmed(request.agent);
+    request.provider = trimmed(request.provider);
+    request.model = trimmed(request.model);
+    request.prompt = trimmed(request.prompt);
+    request.start_at = trimmed(request.start_at);
+
+    validate_setup_env(&request.env).map_err(ApiError::bad_request)?;
+    state
+        .sessions
+        .for_user(user_id)
+        .validate_mounts(&request.mounts)
+        .await
+        .map_err(|e| ApiError::bad_request(format!("{e:#}")))?;
+    request.workspace_path = match trimmed(request.workspace_path) {
+        Some(path) => Some(checked_workspace_path(state, user_id, &path).await?),
+        None => None,
+    };
+    if request.prompt.is_some() && request.workspace_path.is_none() {
+        return Err(ApiError::bad_request(
+            "workspace_path is required to send a prompt",
+        ));
+    }
+    if let Some(start_at) = &request.start_at
+        && crate::db::parse_timestamp(start_at).is_none()
+    {
+        return Err(ApiError::bad_request(
+            "start_at must be an RFC 3339 timestamp",
+        ));
+    }
+    Ok(request)
+}
+
+/// The caller's drafts. Drafts are not sessions and do not appear in the
+/// session list.
+#[instrument(skip(state, user))]
+pub async fn list_session_drafts(
+    State(state): State<AppState>,
+    user: CurrentUser,
+) -> ApiResult<Json<Vec<SessionDraft>>> {
+    Ok(Json(drafts(&state)?.list_for_user(user.id()).await?))
+}
+
+#[instrument(skip(state, user, request))]
+pub async fn create_session_draft(
+    State(state): State<AppState>,
+    user: CurrentUser,
+    Json(request): Json<SaveSessionDraftRequest>,
+) -> ApiResult<(StatusCode, Json<SessionDraft>)> {
+    let repo = drafts(&state)?;
+    let request = checked(&state, user.id(), request).await?;
+    let draft = repo.create(user.id(), &request).await?;
+    info!(draft_id = %draft.id, start_at = ?draft.start_at, "Created session draft");
+    Ok((StatusCode::CREATED, Json(draft)))
+}
+
+/// A draft of the caller's or one shared with them for review.
+#[instrument(skip(state, user))]
+pub async fn get_session_draft(
+    State(state): State<AppState>,
+    user: CurrentUser,
+    Path(draft_id): Path<String>,
+) -> ApiResult<Json<SessionDraft>> {
+    let repo = drafts(&state)?;
+    let draft = repo
+        .get(&draft_id)
+        .await?
+        .ok_or_else(|| ApiError::not_found(format!("Session draft {draft_id} not found")))?;
+    if draft.user_id != user.id() && !repo.is_shared_with(&draft_id, user.id()).await? {
+        return Err(ApiError::not_found(format!(
+            "Session draft {draft_id} not found"
+        )));
+    }
+    Ok(Json(draft))
+}
+
+/// Replace a draft's configuration. Editing a failed draft reopens it.
+#[instrument(skip(state, user, request))]
+pub async fn update_session_draft(
+    State(state): State<AppState>,
+    user: CurrentUser,
+    Path(draft_id): Path<String>,
+    Json(request): Json<SaveSessionDraftRequest>,
+) -> ApiResult<Json<SessionDraft>> {
+    let repo = drafts(&state)?;
+    owned_draft(repo, user.id(), &draft_id).await?;
+    let request = checked(&state, user.id(), request).await?;
+    let draft = repo
+        .update(&draft_id, user.id(), &request)
+        .await?
+        .ok_or_else(|| {
+            ApiError::conflict(format!("Session draft {draft_id} has already been started"))
+        })?;
+    info!(draft_id = %draft_id, "Updated session draft");
+    Ok(Json(draft))
+}
+
+/// Delete a draft. The session a started draft became is kept.
+#[instrument(skip(state, user))]
+pub async fn delete_session_draft(
+    State(state): State<AppState>,
+    user: CurrentUser,
+    Path(draft_id): Path<String>,
+) -> ApiResult<StatusCode> {
+    if !drafts(&state)?.delete(&draft_id, user.id()).await? {
+        return Err(ApiError::not_found(format!(
+            "Session draft {draft_id} not found"
+        )));
+    }
+    info!(draft_id = %draft_id, "Deleted session draft");
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Start a draft now: create its session and send its prompt.
+#[instrument(skip(state, user))]
+pub async fn start_session_draft(
+    State(state): State<AppState>,
+    user: CurrentUser,
+    Path(draft_id): Path<String>,
+) -> ApiResult<Json<SessionDraft>> {
+    let repo = drafts(&state)?;
+    let draft = owned_draft(repo, user.id(), &draft_id).await?;
+    if !repo.claim(&draft_id).await? {
+        return Err(ApiError::conflict(format!(
+            "Session draft {draft_id} is already {}",
+            draft.status.as_str()
+        )));
+    }
+    start_claimed(&state, repo, &draft).await?;
+    repo.get(&draft_id)
+        .await?
+        .map(Json)
+        .ok_or_else(|| ApiError::not_found(format!("Session draft {draft_id} not found")))
+}
+
+/// Create the session of a claimed draft and send its prompt, then record
+/// the outcome on the draft.
+async fn start_claimed(
+    state: &AppState,
+    repo: &SessionDraftRepository,
+    draft: &SessionDraft,
+) -> anyhow::Result<()> {
+    let session = state
+        .sessions
+        .for_user(&draft.user_id)
+        .create_configured_session(
+            CreateSessionRequest {
+                workspace_path: draft.workspace_path.clone(),
+                image: draft.image.clone(),
+                agent: draft.agent.clone(),
+                env: HashMap::new(),
+                sandbox_profile: None,
+            },
+            SessionSetup {
+                provider: draft.provider.clone(),
+                model: draft.model.clone(),
+                env: draft.env.clone(),
+                mounts: draft.mounts.clone(),
+                ..Default::default()
+            },
+        )
+        .await;
+    let session = match session {
+        Ok(session) => session,
+        Err(e) => {
+            repo.mark_failed(&draft.id, &format!("{e:#}")).await?;
+            return Err(e);
+        }
+    };
+    info!(draft_id = %draft.id, session_id = %session.id, "Started session draft");
+
+    // The session exists from here on; a prompt that cannot be sent is
+    // reported but does not fail the draft.
+    let chat_session_id = match (&draft.prompt, &draft.workspace_path) {
+        (Some(prompt), Some(workspace_path)) => {
+            match send_prompt(state, draft, workspace_path, prompt).await {
+                Ok(chat_session_id) => Some(chat_session_id),
+                Err(e) => {
+                    warn!(draft_id = %draft.id, "Failed to send the draft's prompt: {e:#}");
+                    notify(
+                        state,
+                        &draft.user_id,
+                        "warning",
+                        "Draft prompt not sent",
+                        format!("The session started, but its prompt could not be sent: {e:#}"),
+                        draft,
+                    )
+                    .await;
+                    None
+                }
+            }
+        }
+        _ => None,
+    };
+    repo.mark_started(&draft.id, &session.id, chat_session_id.as_deref())
+        .await?;
+    Ok(())
+}
+
+/// Open an agent session in the draft's workspace and send the prompt.
+/// Returns the agent session's ID.
+async fn send_prompt(
+    state: &AppState,
+    draft: &SessionDraft,
+    workspace_path: &str,
+    prompt: &str,
+) -> anyhow::Result<String> {
+    let chat_session_id = uuid::Uuid::new_v4().to_string();
+    let runner = open_agent_session(
+        state,
+        &draft.user_id,
+        workspace_path,
+        &chat_session_id,
+        RunnerPiSessionConfig {
+            cwd: PathBuf::from(workspace_path),
+            provider: draft.provider.clone(),
+            model: draft.model.clone(),
+            session_file: None,
+            continue_session: None,
+            env: HashMap::new(),
+            harness: None,
+            sandbox_profile: None,
+        },
+    )
+    .await?;
+    runner
+        .agent_prompt(&chat_session_id, prompt, None)
+        .await
+        .context("sending prompt")?;
+    Ok(chat_session_id)
+}
+
+async fn notify(
+    state: &AppState,
+    user_id: &str,
+    level: &str,
+    title: &str,
+    message: String,
+    draft: &SessionDraft,
+) {
+    state
+        .ws_hub
+        .send_to_user(
+            user_id,
+            WsEvent::Notification {
+                level: level.to_string(),
+                title: title.to_string(),
+                message,
+                category: "session.draft".to_string(),
+                detail: Some(serde_json::json!({ "draft_id": draft.id })),
+            },
+        )
+        .await;
+}
+
+/// Start drafts whose `start_at` has passed, every 30 seconds. Drafts left
+/// starting by a restart are marked failed first.
+pub async fn run_scheduled_session_drafts(state: AppState) {
+    let Some(repo) = state.session_drafts.clone() else {
+        return;
+    };
+    match repo.fail_interrupted().await {
+        Ok(0) => {}
+        Ok(count) => warn!("Marked {count} interrupted session draft(s) as failed"),
+        Err(e) => warn!("Failed to reset interrupted session drafts: {e:#}"),
+    }
+    let mut interval = tokio::time::interval(DRAFT_POLL_INTERVAL);
+    loop {
+        interval.tick().await;
+        let due = match repo.due(Utc::now()).await {
+            Ok(due) => due,
+            Err(e) => {
+                warn!("Failed to look up due session drafts: {e:#}");
+                continue;
+            }
+        };
+        for draft in due {
+            match repo.claim(&draft.id).await {
+                Ok(true) => {}
+                Ok(false) => continue,
+                Err(e) => {
+                    warn!(draft_id = %draft.id, "Failed to claim session draft: {e:#}");
+                    continue;
+                }
+            }
+            let state = state.clone();
+            let repo = repo.clone();
+            tokio::spawn(async move {
+                let name = draft.name.as_deref().unwrap_or("A session draft");
+                match start_claimed(&state, &repo, &draft).await {
+                    Ok(()) => {
+                        notify(
+                            &state,
+                            &draft.user_id,
+                            "info",
+                            "Scheduled session started",
+                            format!("{name} was started as scheduled"),
+                            &draft,
+                        )
+                        .await
+                    }
+                    Err(e) => {
+                        warn!(draft_id = %draft.id, "Failed to start scheduled session draft: {e:#}");
+                        notify(
+                            &state,
+                            &draft.user_id,
+                            "error",
+                            "Scheduled session failed to start",
+                            format!("{name} could not be started: {e:#}"),
+                            &draft,
+                        )
+                        .await
+                    }
+                }
+            });
+        }
+    }
+}
+
+/// Let another user review a draft (read only).
+#[instrument(skip(state, user, request))]
+pub async fn share_session_draft(
+    State(state): State<AppState>,
+    user: CurrentUser,
+    Path(draft_id): Path<String>,
+    Json(request): Json<ShareSessionDraftRequest>,
+) -> ApiResult<(StatusCode, Json<DraftShare>)> {
+    let repo = drafts(&state)?;
+    let target = request.user_id.trim();
+    if target.is_empty() {
+        return Err(ApiError::bad_request("user_id is required"));
+    }
+    if target == user.id() {
+        return Err(ApiError::bad_request(
+            "You cannot share a draft with yourself",
+        ));
+    }
+    let draft = owned_draft(repo, user.id(), &draft_id).await?;
+    if state.users.get_user(target).await?.is_none() {
+        return Err(ApiError::not_found(format!("User {target} not found")));
+    }
+
+    let share = repo.share(&draft_id, target).await?;
+    info!(draft_id = %draft_id, user_id = %target, "Shared session draft");
+    notify(
+        &state,
+        target,
+        "info",
+        "Session draft shared with you",
+        format!(
+            "{} asked you to review {}",
+            user.display_name(),
+            draft.name.as_deref().unwrap_or("a session draft")
+        ),
+        &draft,
+    )
+    .await;
+    Ok((StatusCode::CREATED, Json(share)))
+}
+
+/// Reviewers of one of the caller's drafts.
+#[instrument(skip(state, user))]
+pub async fn list_session_draft_shares(
+    State(state): State<AppState>,
+    user: CurrentUser,
+    Path(draft_id): Path<String>,
+) -> ApiResult<Json<Vec<DraftShare>>> {
+    let repo = drafts(&state)?;
+    owned_draft(repo, user.id(), &draft_id).await?;
+    Ok(Json(repo.list_shares(&draft_id).await?))
+}
+
+#[instrument(skip(state, user))]
+pub async fn revoke_session_draft_share(
+    State(state): State<AppState>,
+    user: CurrentUser,
+    Path((draft_id, user_id)): Path<(String, String)>,
+) -> ApiResult<StatusCode> {
+    let repo = drafts(&state)?;
+    owned_draft(repo, user.id(), &draft_id).await?;
+    if !repo.unshare(&draft_id, &user_id).await? {
+        return Err(ApiError::not_found(format!(
+            "Session draft {draft_id} is not shared with {user_id}"
+        )));
+    }
+    info!(draft_id = %draft_id, user_id = %user_id, "Stopped sharing session draft");
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Drafts other users shared with the caller for review.
+#[instrument(skip(state, user))]
+pub async fn list_session_drafts_shared_with_me(
+    State(state): State<AppState>,
+    user: CurrentUser,
+) -> ApiResult<Json<Vec<SessionDraft>>> {
+    Ok(Json(drafts(&state)?.list_shared_with(user.id()).await?))
+}
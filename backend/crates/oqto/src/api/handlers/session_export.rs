@@ -0,0 +1,148 @@
+//! Conversation export handlers.
+
+use axum::{
+    extract::{Path, Query, State},
+    http::header,
+    response::{IntoResponse, Response},
+};
+use oqto_runner::protocol::WorkspaceChatMessagesSource;
+use serde::Deserialize;
+use tracing::{info, instrument, warn};
+
+use crate::auth::CurrentUser;
+use crate::history::export::{ConversationExport, ExportAttachment, ExportFormat};
+
+use super::bookmarks::session_access;
+use super::chat::session_runner;
+use crate::api::error::{ApiError, ApiResult};
+use crate::api::state::AppState;
+
+/// Attachment bytes embedded in one export. Attachments past it are linked
+/// instead.
+const MAX_EMBEDDED_ATTACHMENT_BYTES: u64 = 25 * 1024 * 1024;
+
+#[derive(Debug, Deserialize)]
+pub struct SessionExportQuery {
+    #[serde(default)]
+    pub format: ExportFormat,
+    /// If set, read the session from the shared workspace's runner.
+    pub shared_workspace_id: Option<String>,
+}
+
+/// Download a whole conversation (messages, tool calls, file edits and
+/// attachments) as Markdown, JSON or a standalone HTML page. Messages the
+/// session owner made private are left out for everyone else, along with
+/// the attachments of their tool calls.
+///
+/// GET /api/sessions/{session_id}/export?format=markdown|json|html
+#[instrument(skip(state, user))]
+pub async fn export_session(
+    State(state): State<AppState>,
+    user: CurrentUser,
+    Path(session_id): Path<String>,
+    Query(query): Query<SessionExportQuery>,
+) -> ApiResult<Response> {
+    let workspace_id = session_access(
+        &state,
+        user.id(),
+        &session_id,
+        query.shared_workspace_id.as_deref(),
+    )
+    .await?;
+    let runner = session_runner(&state, user.id(), &session_id, workspace_id.as_deref()).await?;
+
+    let session = runner
+        .get_workspace_chat_session(&session_id)
+        .await
+        .map_err(|e| ApiError::internal(format!("runner get session failed: {e:#}")))?
+        .session;
+    let mut messages = runner
+        .get_workspace_chat_session_messages(
+            &session_id,
+            false,
+            None,
+            WorkspaceChatMessagesSource::Authoritative,
+        )
+        .await
+        .map_err(|e| ApiError::internal(format!("runner get messages failed: {e:#}")))?
+        .messages;
+    let hidden = super::private_messages::hidden_from(&state, &session_id, user.id()).await?;
+    let hidden_tool_calls = crate::private_messages::hidden_tool_calls(&messages, &hidden);
+    crate::private_messages::elide(&mut messages, &hidden);
+
+    let artifacts = match runner.list_artifacts(&session_id).await {
+        Ok(response) => response.artifacts,
+        Err(e) => {
+            warn!(session_id = %session_id, "Exporting without attachments: {e:#}");
+            Vec::new()
+        }
+    };
+    let mut budget = MAX_EMBEDDED_ATTACHMENT_BYTES;
+    let mut attachments = Vec::with_capacity(artifacts.len());
+    for artifact in artifacts {
+        if crate::private_messages::is_attachment_hidden(
+            artifact.tool_call_id.as_deref(),
+            &hidden_tool_calls,
+        ) {
+            continue;
+        }
+        let mut data_base64 = None;
+        if artifact.size_bytes <= budget {
+            match runner.get_artifact(&artifact.id, false).await {
+                // Artifact IDs are runner-wide.
+                Ok(content) if content.session_id == session_id => {
+                    budget -= artifact.size_bytes;
+                    data_base64 = Some(content.data_base64);
+                }
+                Ok(_) => continue,
+                Err(e) => warn!(
+                    artifact_id = %artifact.id,
+                    "Linking attachment instead of embedding it: {e:#}"
+                ),
+            }
+        }
+        attachments.push(ExportAttachment {
+            id: artifact.id,
+            filename: artifact.filename,
+            mime_type: artifact.mime_type,
+            size_bytes: artifact.size_bytes,
+            tool_call_id: artifact.tool_call_id,
+            created_at: artifact.created_at,
+            url: artifact.url,
+            data_base64,
+        });
+    }
+
+    let export = ConversationExport {
+        session_id: session_id.clone(),
+        title: session.as_ref().and_then(|s| s.title.clone()),
+        workspace_path: session.map(|s| s.workspace_path),
+        exported_at: chrono::Utc::now(),
+        messages,
+        attachments,
+    };
+    let format = query.format;
+    let file_name = export.file_name(format);
+    let message_count = export.messages.len();
+    let body = tokio::task::spawn_blocking(move || export.render(format))
+        .await
+        .map_err(|e| ApiError::internal(format!("export rendering failed: {e}")))??;
+    info!(
+        session_id = %session_id,
+        format = format.extension(),
+        messages = message_count,
+        "Exported session"
+    );
+
+    Ok((
+        [
+            (header::CONTENT_TYPE, format.content_type().to_string()),
+            (
+                header::CONTENT_DISPOSITION,
+                format!("attachment; filename=\"{file_name}\""),
+            ),
+        ],
+        body,
+    )
+        .into_response())
+}
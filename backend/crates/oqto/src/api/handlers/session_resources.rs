@@ -0,0 +1,160 @@
+//! Session resource telemetry handlers.
+
+use std::time::{Duration, Instant};
+
+use axum::{
+    Json,
+    extract::{Path, State},
+};
+use tracing::{debug, instrument, warn};
+
+use crate::auth::CurrentUser;
+use crate::session::resources::{ResourceHistory, SamplingSettings, UpdateSamplingRequest};
+use crate::session::{SessionResourceMonitor, SessionStatus};
+use crate::ws::types::WsEvent;
+
+use crate::api::error::{ApiError, ApiResult};
+use crate::api::state::AppState;
+
+fn monitor(state: &AppState) -> ApiResult<&SessionResourceMonitor> {
+    state
+        .session_resources
+        .as_deref()
+        .ok_or_else(|| ApiError::not_found("Resource telemetry is disabled"))
+}
+
+/// Check that the session is the caller's and running.
+async fn running_session(state: &AppState, user_id: &str, session_id: &str) -> ApiResult<()> {
+    let session = state
+        .sessions
+        .get_session_for_user(user_id, session_id)
+        .await?
+        .ok_or_else(|| ApiError::not_found(format!("Session {session_id} not found")))?;
+    if session.status != SessionStatus::Running {
+        return Err(ApiError::conflict(format!(
+            "Session {session_id} is not running"
+        )));
+    }
+    Ok(())
+}
+
+/// Recent resource usage of a running session, oldest first, for
+/// sparklines.
+///
+/// GET /api/sessions/{session_id}/resources
+#[instrument(skip(state, user))]
+pub async fn get_session_resources(
+    State(state): State<AppState>,
+    user: CurrentUser,
+    Path(session_id): Path<String>,
+) -> ApiResult<Json<ResourceHistory>> {
+    let monitor = monitor(&state)?;
+    running_session(&state, user.id(), &session_id).await?;
+    // Running but not picked up by the sampler yet.
+    let history = monitor
+        .history(&session_id)
+        .unwrap_or_else(|| ResourceHistory {
+            session_id: session_id.clone(),
+            sampling: SamplingSettings {
+                interval_secs: monitor.config().interval_secs.max(1),
+                stream: true,
+            },
+            samples: Vec::new(),
+        });
+    Ok(Json(history))
+}
+
+/// Change how often a session is sampled, or pause its
+/// `session.resources` events, until the session stops.
+///
+/// PUT /api/sessions/{session_id}/resources/sampling
+#[instrument(skip(state, user))]
+pub async fn update_session_resource_sampling(
+    State(state): State<AppState>,
+    user: CurrentUser,
+    Path(session_id): Path<String>,
+    Json(request): Json<UpdateSamplingRequest>,
+) -> ApiResult<Json<SamplingSettings>> {
+    let monitor = monitor(&state)?;
+    running_session(&state, user.id(), &session_id).await?;
+    monitor
+        .set_sampling(&session_id, &request, Instant::now())
+        .map(Json)
+        .ok_or_else(|| ApiError::conflict("Session is not sampled yet, retry shortly"))
+}
+
+/// Sample running sessions as they fall due, push the samples to their
+/// owners and keep busy sessions from idling out. Runs until shutdown.
+pub async fn run_resource_telemetry(state: AppState) {
+    let Some(monitor) = state.session_resources.clone() else {
+        return;
+    };
+    let mut interval = tokio::time::interval(Duration::from_secs(
+        monitor.config().min_interval_secs.max(1),
+    ));
+    interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+    loop {
+        interval.tick().await;
+        let sessions = match state.sessions.list_active_sessions().await {
+            Ok(sessions) => sessions,
+            Err(e) => {
+                warn!("Resource telemetry: failed to list sessions: {e:#}");
+                continue;
+            }
+        };
+        let running: Vec<_> = sessions
+            .into_iter()
+            .filter(|s| s.status == SessionStatus::Running)
+            .collect();
+        let ids: Vec<(String, String)> = running
+            .iter()
+            .map(|s| (s.id.clone(), s.user_id.clone()))
+            .collect();
+        let due = monitor.due(&ids, Instant::now());
+        // `docker stats` takes a second or two per container.
+        let readings =
+            futures::future::join_all(running.iter().filter(|s| due.contains(&s.id)).map(
+                |session| async {
+                    (
+                        session,
+                        state.sessions.sample_session_resources(session).await,
+                    )
+                },
+            ))
+            .await;
+
+        for (session, reading) in readings {
+            let reading = match reading {
+                Ok(reading) => reading,
+                Err(e) => {
+                    debug!(session_id = %session.id, "Failed to sample resources: {e:#}");
+                    continue;
+                }
+            };
+            let Some(recorded) = monitor.record(&session.id, reading, Instant::now()) else {
+                continue;
+            };
+            if recorded.touch_activity
+                && let Err(e) = state
+                    .sessions
+                    .for_user(&recorded.user_id)
+                    .touch_session_activity(&session.id)
+                    .await
+            {
+                warn!(session_id = %session.id, "Failed to record session activity: {e:#}");
+            }
+            if recorded.stream {
+                state
+                    .ws_hub
+                    .send_to_user(
+                        &recorded.user_id,
+                        WsEvent::SessionResources {
+                            session_id: session.id.clone(),
+                            sample: recorded.sample,
+                        },
+                    )
+                    .await;
+            }
+        }
+    }
+}
@@ -0,0 +1,163 @@
+//! Session sharing handlers: owners grant other users access to a session.
+
+use axum::{
+    Json,
+    extract::{Path, State},
+    http::StatusCode,
+};
+use tracing::{info, instrument};
+
+use crate::auth::CurrentUser;
+use crate::session_shares::{SessionShare, SessionShareRepository, ShareSessionRequest};
+use crate::session_target::SessionTargetScope;
+use crate::ws::types::WsEvent;
+
+use super::chat::session_runner;
+use super::impersonation::refuse_impersonated;
+use crate::api::error::{ApiError, ApiResult};
+use crate::api::state::AppState;
+use crate::api::ws_multiplexed::detach_participant;
+
+fn shares(state: &AppState) -> ApiResult<&SessionShareRepository> {
+    state
+        .session_shares
+        .as_deref()
+        .ok_or_else(|| ApiError::not_found("Session sharing is not available"))
+}
+
+/// Error unless the caller owns the session. Sessions of shared workspaces
+/// are shared through workspace membership instead.
+async fn require_owner(state: &AppState, user_id: &str, session_id: &str) -> ApiResult<()> {
+    if state
+        .session_targets
+        .get(session_id)
+        .await?
+        .is_some_and(|record| record.scope == SessionTargetScope::SharedWorkspace)
+    {
+        return Err(ApiError::bad_request(
+            "Sessions in shared workspaces are shared with the workspace members",
+        ));
+    }
+    let runner = session_runner(state, user_id, session_id, None).await?;
+    runner
+        .get_workspace_chat_session(session_id)
+        .await
+        .map_err(|e| ApiError::internal(format!("runner get session failed: {e:#}")))?
+        .session
+        .ok_or_else(|| ApiError::not_found(format!("Session {session_id} not found")))?;
+    Ok(())
+}
+
+/// Share a session with another user, or change their access.
+#[instrument(skip(state, user, request))]
+pub async fn share_session(
+    State(state): State<AppState>,
+    user: CurrentUser,
+    Path(session_id): Path<String>,
+    Json(request): Json<ShareSessionRequest>,
+) -> ApiResult<(StatusCode, Json<SessionShare>)> {
+    refuse_impersonated(&user, "Sessions cannot be shared")?;
+    let repo = shares(&state)?;
+    let target = request.user_id.trim();
+    if target.is_empty() {
+        return Err(ApiError::bad_request("user_id is required"));
+    }
+    if target == user.id() {
+        return Err(ApiError::bad_request(
+            "You cannot share a session with yourself",
+        ));
+    }
+    require_owner(&state, user.id(), &session_id).await?;
+    if state.users.get_user(target).await?.is_none() {
+        return Err(ApiError::not_found(format!("User {target} not found")));
+    }
+
+    let share = repo
+        .upsert(&session_id, user.id(), target, request.access)
+        .await?;
+    info!(
+        session_id = %session_id,
+        user_id = %target,
+        access = share.access.as_str(),
+        "Shared session"
+    );
+    state
+        .ws_hub
+        .send_to_user(
+            target,
+            WsEvent::Notification {
+                level: "info".to_string(),
+                title: "Session shared with you".to_string(),
+                message: format!("{} shared a session with you", user.display_name()),
+                category: "session.share".to_string(),
+                detail: Some(serde_json::json!({
+                    "session_id": session_id,
+                    "owner_user_id": user.id(),
+                    "access": share.access,
+                })),
+            },
+        )
+        .await;
+    Ok((StatusCode::CREATED, Json(share)))
+}
+
+/// Users a session is shared with (owner only).
+#[instrument(skip(state, user))]
+pub async fn list_session_shares(
+    State(state): State<AppState>,
+    user: CurrentUser,
+    Path(session_id): Path<String>,
+) -> ApiResult<Json<Vec<SessionShare>>> {
+    let repo = shares(&state)?;
+    require_owner(&state, user.id(), &session_id).await?;
+    Ok(Json(repo.list_for_session(&session_id).await?))
+}
+
+/// Stop sharing a session with a user. The owner can remove anyone; a
+/// participant can remove themselves.
+#[instrument(skip(state, user))]
+pub async fn revoke_session_share(
+    State(state): State<AppState>,
+    user: CurrentUser,
+    Path((session_id, user_id)): Path<(String, String)>,
+) -> ApiResult<StatusCode> {
+    let repo = shares(&state)?;
+    let not_found =
+        || ApiError::not_found(format!("Session {session_id} is not shared with {user_id}"));
+    let share = repo
+        .get(&session_id, &user_id)
+        .await?
+        .ok_or_else(not_found)?;
+    if share.owner_user_id != user.id() && share.user_id != user.id() {
+        return Err(not_found());
+    }
+
+    repo.delete(&session_id, &user_id).await?;
+    detach_participant(&session_id, &user_id).await;
+    info!(session_id = %session_id, user_id = %user_id, "Revoked session share");
+    if share.user_id != user.id() {
+        state
+            .ws_hub
+            .send_to_user(
+                &user_id,
+                WsEvent::Notification {
+                    level: "info".to_string(),
+                    title: "Session no longer shared".to_string(),
+                    message: format!("{} stopped sharing a session with you", user.display_name()),
+                    category: "session.share".to_string(),
+                    detail: Some(serde_json::json!({ "session_id": session_id })),
+                },
+            )
+            .await;
+    }
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Sessions other users shared with the caller.
+#[instrument(skip(state, user))]
+pub async fn list_shared_with_me(
+    State(state): State<AppState>,
+    user: CurrentUser,
+) -> ApiResult<Json<Vec<SessionShare>>> {
+    Ok(Json(shares(&state)?.list_for_user(user.id()).await?))
+}
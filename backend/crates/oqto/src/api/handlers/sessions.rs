@@ -5,11 +5,15 @@ use axum::{
     extract::{Path, State},
     http::StatusCode,
 };
+use chrono::Utc;
+use oqto_protocol::events::{Event, EventPayload};
 use serde::{Deserialize, Serialize};
-use tracing::{info, instrument};
+use tokio::sync::broadcast::error::RecvError;
+use tracing::{info, instrument, warn};
 
 use crate::auth::CurrentUser;
-use crate::session::{CreateSessionRequest, Session};
+use crate::session::{CloneSessionRequest, CreateSessionRequest, Session, SessionSetup};
+use crate::ws::types::WsEvent;
 
 use crate::api::error::{ApiError, ApiResult};
 use crate::api::state::AppState;
@@ -98,6 +102,57 @@ pub async fn create_session(
     Ok((StatusCode::CREATED, Json(response)))
 }
 
+/// Create a new session with the setup of an existing one: same image,
+/// agent, model and environment, in the same workspace, a copy of it, or a
+/// clean one. The clone records which session it came from.
+#[instrument(skip(state, request), fields(workspace = ?request.workspace))]
+pub async fn clone_session(
+    State(state): State<AppState>,
+    user: CurrentUser,
+    Path(session_id): Path<String>,
+    Json(request): Json<CloneSessionRequest>,
+) -> ApiResult<(StatusCode, Json<SessionWithUrls>)> {
+    crate::session::validate_setup_env(&request.env).map_err(ApiError::bad_request)?;
+    let sessions = state.sessions.for_user(user.id());
+    if sessions.get_session(&session_id).await?.is_none() {
+        return Err(ApiError::not_found(format!(
+            "Session {} not found",
+            session_id
+        )));
+    }
+
+    let session = sessions.clone_session(&session_id, request).await?;
+    info!(session_id = %session.id, source = %session_id, "Cloned session");
+
+    let response = SessionWithUrls::from_session(session, "localhost");
+    Ok((StatusCode::CREATED, Json(response)))
+}
+
+/// A session's setup with its clones.
+#[derive(Debug, Serialize)]
+pub struct SessionSetupResponse {
+    #[serde(flatten)]
+    pub setup: SessionSetup,
+    /// Sessions cloned from this one.
+    pub clones: Vec<String>,
+}
+
+/// Get the setup a session was created with and where it was cloned from.
+#[instrument(skip(state, user))]
+pub async fn get_session_setup(
+    State(state): State<AppState>,
+    user: CurrentUser,
+    Path(session_id): Path<String>,
+) -> ApiResult<Json<SessionSetupResponse>> {
+    let sessions = state.sessions.for_user(user.id());
+    let setup = sessions
+        .get_session_setup(&session_id)
+        .await?
+        .ok_or_else(|| ApiError::not_found(format!("Session {} not found", session_id)))?;
+    let clones = sessions.list_session_clones(&setup.session_id).await?;
+    Ok(Json(SessionSetupResponse { setup, clones }))
+}
+
 /// Stop a session.
 #[instrument(skip(state))]
 pub async fn stop_session(
@@ -328,7 +383,7 @@ pub struct BrowserActionRequest {
 #[instrument(skip(state))]
 pub async fn start_browser(
     State(state): State<AppState>,
-    _user: CurrentUser,
+    user: CurrentUser,
     Json(request): Json<BrowserNavigateRequest>,
 ) -> ApiResult<Json<BrowserStartResponse>> {
     if !state.sessions.agent_browser_enabled() {
@@ -343,6 +398,7 @@ pub async fn start_browser(
         .sessions
         .navigate_browser(
             &browser_session_id,
+            user.id(),
             &request.url,
             request.viewport_width,
             request.viewport_height,
@@ -415,3 +471,75 @@ pub async fn check_all_updates(
     info!(count = statuses.len(), "Checked all sessions for updates");
     Ok(Json(statuses))
 }
+
+/// Announce sessions moved to other ports after a port conflict to their
+/// owners as `session.port_conflict`. Runs until shutdown.
+pub async fn run_port_conflict_events(state: AppState) {
+    let mut events = state.sessions.subscribe_port_conflicts();
+    loop {
+        let event = match events.recv().await {
+            Ok(event) => event,
+            Err(RecvError::Lagged(skipped)) => {
+                warn!("Port conflict events: skipped {skipped} events");
+                continue;
+            }
+            Err(RecvError::Closed) => return,
+        };
+        state
+            .ws_hub
+            .send_to_user(
+                &event.user_id,
+                WsEvent::SessionPortConflict {
+                    session_id: event.session_id,
+                    port: event.port,
+                    holder: event.holder,
+                    previous_ports: event.previous_ports,
+                    ports: event.ports,
+                    attempt: event.attempt,
+                },
+            )
+            .await;
+    }
+}
+
+/// Announce connections the egress policy blocked in session containers to
+/// the session owners as `network.blocked` agent events. Runs until shutdown;
+/// returns at once without an egress policy.
+pub async fn run_blocked_connection_events(state: AppState) {
+    let Some(mut events) = state.sessions.subscribe_blocked_connections() else {
+        return;
+    };
+    loop {
+        let event = match events.recv().await {
+            Ok(event) => event,
+            Err(RecvError::Lagged(skipped)) => {
+                warn!("Blocked connection events: skipped {skipped} events");
+                continue;
+            }
+            Err(RecvError::Closed) => return,
+        };
+        let connection = event.connection;
+        let agent_event = Event {
+            session_id: event.session_id.clone(),
+            runner_id: "local".to_string(),
+            ts: Utc::now().timestamp_millis(),
+            seq: None,
+            payload: EventPayload::NetworkBlocked {
+                host: connection.host,
+                address: connection.address,
+                port: connection.port,
+                reason: connection.reason,
+            },
+        };
+        state
+            .ws_hub
+            .send_to_user(
+                &event.user_id,
+                WsEvent::AgentEvent {
+                    session_id: event.session_id,
+                    event: serde_json::to_value(&agent_event).unwrap_or_default(),
+                },
+            )
+            .await;
+    }
+}
@@ -8,10 +8,15 @@ use axum::{
     extract::{Query, State},
 };
 use serde::Deserialize;
-use tracing::{info, instrument};
+use tracing::{info, instrument, warn};
 
-use crate::auth::{CurrentUser, RequireAdmin};
-use crate::settings::{ConfigUpdate, SettingsScope, SettingsService, SettingsValue};
+use crate::auth::{Access, CurrentUser, Permission};
+use crate::runner::router::{ExecutionTarget, resolve_runner_for_target};
+use crate::settings::{
+    ConfigUpdate, NewSettingsPatch, PatchError, PatchOperation, SessionSyncStatus,
+    SettingsDocument, SettingsHistoryQuery, SettingsPatch, SettingsPatchError, SettingsPatchPage,
+    SettingsScope, SettingsService, SettingsValue,
+};
 
 use crate::api::error::{ApiError, ApiResult};
 use crate::api::state::AppState;
@@ -29,16 +34,16 @@ pub struct SettingsQuery {
 }
 
 /// Get the settings schema for an app, filtered by user permissions.
-#[instrument(skip(state, user))]
+#[instrument(skip(state, access))]
 pub async fn get_settings_schema(
     State(state): State<AppState>,
-    user: CurrentUser,
+    access: Access,
     Query(query): Query<SettingsQuery>,
 ) -> ApiResult<Json<serde_json::Value>> {
+    let user = &access.user;
     let service =
-        resolve_settings_service(&state, &user, &query.app, query.workspace_path.as_deref())
-            .await?;
-    let scope = user_to_scope(&user);
+        resolve_settings_service(&state, user, &query.app, query.workspace_path.as_deref()).await?;
+    let scope = user_to_scope(&access);
 
     let schema = service.get_schema(scope);
 
@@ -47,16 +52,16 @@ pub async fn get_settings_schema(
 }
 
 /// Get current settings values for an app.
-#[instrument(skip(state, user))]
+#[instrument(skip(state, access))]
 pub async fn get_settings_values(
     State(state): State<AppState>,
-    user: CurrentUser,
+    access: Access,
     Query(query): Query<SettingsQuery>,
 ) -> ApiResult<Json<HashMap<String, SettingsValue>>> {
+    let user = &access.user;
     let service =
-        resolve_settings_service(&state, &user, &query.app, query.workspace_path.as_deref())
-            .await?;
-    let scope = user_to_scope(&user);
+        resolve_settings_service(&state, user, &query.app, query.workspace_path.as_deref()).await?;
+    let scope = user_to_scope(&access);
 
     let values = service.get_values(scope).await;
 
@@ -65,44 +70,250 @@ pub async fn get_settings_values(
 }
 
 /// Update settings values for an app.
-#[instrument(skip(state, user))]
+#[instrument(skip(state, access))]
 pub async fn update_settings_values(
     State(state): State<AppState>,
-    user: CurrentUser,
+    access: Access,
     Query(query): Query<SettingsQuery>,
     Json(updates): Json<ConfigUpdate>,
 ) -> ApiResult<Json<HashMap<String, SettingsValue>>> {
+    let user = &access.user;
     let service =
-        resolve_settings_service(&state, &user, &query.app, query.workspace_path.as_deref())
-            .await?;
-    let scope = user_to_scope(&user);
+        resolve_settings_service(&state, user, &query.app, query.workspace_path.as_deref()).await?;
+    let scope = user_to_scope(&access);
 
+    let base_version = service.document_version().ok();
+    let mut operations = updates.operations();
     service
         .update_values(updates, scope)
         .await
         .map_err(|e| ApiError::bad_request(format!("Failed to update settings: {}", e)))?;
 
+    if let (Some(base_version), Ok(version)) = (base_version, service.document_version()) {
+        service.redact_operations(&mut operations);
+        record_patch(
+            &state,
+            &query.app,
+            &service,
+            user.id(),
+            &base_version,
+            &version,
+            &operations,
+        )
+        .await;
+    }
+
     // Return updated values
     let values = service.get_values(scope).await;
+    after_settings_write(&state, &query, user.id());
 
     info!(user_id = %user.id(), app = %query.app, "Updated settings");
     Ok(Json(values))
 }
 
-/// Reload settings from disk (admin only).
-#[instrument(skip(state, admin))]
-pub async fn reload_settings(
+/// The whole settings document of an app with its version, for JSON Patch
+/// edits. Settings hidden from the caller are left out.
+#[instrument(skip(state, access))]
+pub async fn get_settings_document(
     State(state): State<AppState>,
-    admin: RequireAdmin,
+    access: Access,
     Query(query): Query<SettingsQuery>,
-) -> ApiResult<Json<serde_json::Value>> {
-    let service = resolve_settings_service(
+) -> ApiResult<Json<SettingsDocument>> {
+    let user = &access.user;
+    let service =
+        resolve_settings_service(&state, user, &query.app, query.workspace_path.as_deref()).await?;
+    let document = service
+        .document(user_to_scope(&access))
+        .map_err(|e| ApiError::internal(format!("Failed to read settings: {e:#}")))?;
+    Ok(Json(document))
+}
+
+/// Apply a JSON Patch (RFC 6902) to an app's settings document.
+///
+/// 409 when `version` is stale or a `test` operation fails; 400 when the
+/// patch is malformed or leaves the document invalid against the schema.
+#[instrument(skip(state, access, patch))]
+pub async fn patch_settings_document(
+    State(state): State<AppState>,
+    access: Access,
+    Query(query): Query<SettingsQuery>,
+    Json(patch): Json<SettingsPatch>,
+) -> ApiResult<Json<SettingsDocument>> {
+    let user = &access.user;
+    let service =
+        resolve_settings_service(&state, user, &query.app, query.workspace_path.as_deref()).await?;
+
+    let applied = service
+        .patch_document(&patch, user_to_scope(&access))
+        .await
+        .map_err(patch_error)?;
+    record_patch(
         &state,
-        &admin.0,
         &query.app,
-        query.workspace_path.as_deref(),
+        &service,
+        user.id(),
+        &applied.base_version,
+        &applied.document.version,
+        &applied.redacted,
     )
-    .await?;
+    .await;
+    after_settings_write(&state, &query, user.id());
+
+    info!(
+        user_id = %user.id(),
+        app = %query.app,
+        operations = patch.operations.len(),
+        version = %applied.document.version,
+        "Patched settings"
+    );
+    Ok(Json(applied.document))
+}
+
+/// Patch history of an app's settings document, newest first. Callers
+/// without `settings.edit` only see their own patches.
+#[instrument(skip(state, access))]
+pub async fn get_settings_history(
+    State(state): State<AppState>,
+    access: Access,
+    Query(query): Query<SettingsQuery>,
+    Query(page): Query<SettingsHistoryQuery>,
+) -> ApiResult<Json<SettingsPatchPage>> {
+    let user = &access.user;
+    let service =
+        resolve_settings_service(&state, user, &query.app, query.workspace_path.as_deref()).await?;
+    let history = state
+        .settings_history
+        .as_ref()
+        .ok_or_else(|| ApiError::not_found("Settings history is not available"))?;
+    let own = match user_to_scope(&access) {
+        SettingsScope::Admin => None,
+        SettingsScope::User => Some(user.id()),
+    };
+    let page = history
+        .list(&service.config_path().to_string_lossy(), own, &page)
+        .await
+        .map_err(|e| ApiError::internal(format!("Failed to list settings history: {e:#}")))?;
+    Ok(Json(page))
+}
+
+fn patch_error(e: SettingsPatchError) -> ApiError {
+    match e {
+        SettingsPatchError::Conflict { .. }
+        | SettingsPatchError::Patch(PatchError::TestFailed(_)) => ApiError::conflict(e.to_string()),
+        SettingsPatchError::Forbidden(_) => ApiError::forbidden(e.to_string()),
+        SettingsPatchError::Patch(_) | SettingsPatchError::Invalid(_) => {
+            ApiError::bad_request(e.to_string())
+        }
+        SettingsPatchError::Internal(e) => {
+            ApiError::internal(format!("Failed to patch settings: {e:#}"))
+        }
+    }
+}
+
+/// Store a change in the patch history. Failures are logged; the change
+/// itself already happened.
+async fn record_patch(
+    state: &AppState,
+    app: &str,
+    service: &SettingsService,
+    user_id: &str,
+    base_version: &str,
+    version: &str,
+    operations: &[PatchOperation],
+) {
+    let Some(history) = &state.settings_history else {
+        return;
+    };
+    let config_path = service.config_path().to_string_lossy().into_owned();
+    let patch = NewSettingsPatch {
+        app,
+        config_path: &config_path,
+        user_id,
+        base_version,
+        version,
+        operations,
+    };
+    if let Err(e) = history.record(&patch).await {
+        warn!(app = %app, "Failed to record settings patch: {e:#}");
+    }
+}
+
+/// Push global Pi config changes into the user's sessions. Workspace-scoped
+/// files live in the workspace, which sessions already see.
+fn after_settings_write(state: &AppState, query: &SettingsQuery, user_id: &str) {
+    if query.workspace_path.is_none() && matches!(query.app.as_str(), "pi-agent" | "pi-models") {
+        spawn_settings_sync(state, user_id);
+    }
+}
+
+/// Outcome of the caller's last Pi settings sync, per session.
+#[instrument(skip(state, user))]
+pub async fn get_settings_sync(
+    State(state): State<AppState>,
+    user: CurrentUser,
+) -> ApiResult<Json<Vec<SessionSyncStatus>>> {
+    let sync = state
+        .settings_sync
+        .as_ref()
+        .ok_or_else(|| ApiError::not_found("Settings sync is not available"))?;
+    Ok(Json(sync.last_sync(user.id())))
+}
+
+/// Push the Pi settings into the caller's running sessions now.
+#[instrument(skip(state, user))]
+pub async fn sync_settings(
+    State(state): State<AppState>,
+    user: CurrentUser,
+) -> ApiResult<Json<Vec<SessionSyncStatus>>> {
+    let sync = state
+        .settings_sync
+        .as_ref()
+        .ok_or_else(|| ApiError::not_found("Settings sync is not available"))?;
+    let runner = personal_runner(&state, user.id()).await;
+    let statuses = sync
+        .sync_user(user.id(), runner.as_ref())
+        .await
+        .map_err(|e| ApiError::internal(format!("Settings sync failed: {e:#}")))?;
+    Ok(Json(statuses))
+}
+
+/// Sync the user's sessions in the background after a settings change.
+fn spawn_settings_sync(state: &AppState, user_id: &str) {
+    let Some(sync) = state.settings_sync.clone() else {
+        return;
+    };
+    let state = state.clone();
+    let user_id = user_id.to_string();
+    tokio::spawn(async move {
+        let runner = personal_runner(&state, &user_id).await;
+        if let Err(e) = sync.sync_user(&user_id, runner.as_ref()).await {
+            warn!(user_id = %user_id, "Settings sync failed: {e:#}");
+        }
+    });
+}
+
+/// The user's runner, if one is reachable. Sessions without one only get
+/// their files synced.
+async fn personal_runner(
+    state: &AppState,
+    user_id: &str,
+) -> Option<oqto_runner::client::RunnerClient> {
+    resolve_runner_for_target(state, user_id, &ExecutionTarget::Personal)
+        .await
+        .ok()
+        .flatten()
+}
+
+/// Reload settings from disk (admin or `settings.edit`).
+#[instrument(skip(state, access))]
+pub async fn reload_settings(
+    State(state): State<AppState>,
+    access: Access,
+    Query(query): Query<SettingsQuery>,
+) -> ApiResult<Json<serde_json::Value>> {
+    let user = access.require(Permission::SettingsEdit)?;
+    let service =
+        resolve_settings_service(&state, user, &query.app, query.workspace_path.as_deref()).await?;
 
     service
         .reload()
@@ -113,9 +324,10 @@ pub async fn reload_settings(
     Ok(Json(serde_json::json!({ "status": "reloaded" })))
 }
 
-/// Convert user role to settings scope.
-fn user_to_scope(user: &CurrentUser) -> SettingsScope {
-    if user.is_admin() {
+/// Settings scope of the user: admins and holders of `settings.edit` see
+/// and edit admin settings.
+fn user_to_scope(access: &Access) -> SettingsScope {
+    if access.allows(Permission::SettingsEdit) {
         SettingsScope::Admin
     } else {
         SettingsScope::User
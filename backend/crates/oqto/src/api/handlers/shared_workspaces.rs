@@ -10,11 +10,12 @@ use tracing::info;
 use crate::auth::CurrentUser;
 use crate::shared_workspace::{
     AddMemberRequest, AdminSharedWorkspaceInfo, ConvertToSharedRequest,
-    CreateSharedWorkspaceRequest, CreateSharedWorkspaceWorkdirRequest, SharedWorkspaceInfo,
-    SharedWorkspaceMemberInfo, TransferOwnershipRequest, UpdateMemberRequest,
-    UpdateSharedWorkspaceRequest,
+    CreateSharedWorkspaceRequest, CreateSharedWorkspaceWorkdirRequest, SharePermissions,
+    SharedWorkspaceInfo, SharedWorkspaceMemberInfo, TransferOwnershipRequest,
+    UpdateMemberPermissionsRequest, UpdateMemberRequest, UpdateSharedWorkspaceRequest,
 };
 
+use super::impersonation::refuse_impersonated;
 use crate::api::error::{ApiError, ApiResult};
 use crate::api::state::AppState;
 
@@ -28,6 +29,9 @@ pub async fn create_shared_workspace(
     user: CurrentUser,
     Json(request): Json<CreateSharedWorkspaceRequest>,
 ) -> ApiResult<Json<serde_json::Value>> {
+    if !request.member_ids.is_empty() {
+        refuse_impersonated(&user, "Members cannot be added")?;
+    }
     let service = state
         .shared_workspaces
         .as_ref()
@@ -213,6 +217,7 @@ pub async fn add_shared_workspace_member(
     Path(workspace_id): Path<String>,
     Json(request): Json<AddMemberRequest>,
 ) -> ApiResult<Json<serde_json::Value>> {
+    refuse_impersonated(&user, "Members cannot be added")?;
     let service = state
         .shared_workspaces
         .as_ref()
@@ -233,6 +238,7 @@ pub async fn update_shared_workspace_member(
     Path((workspace_id, target_user_id)): Path<(String, String)>,
     Json(request): Json<UpdateMemberRequest>,
 ) -> ApiResult<Json<serde_json::Value>> {
+    refuse_impersonated(&user, "Member roles cannot be changed")?;
     let service = state
         .shared_workspaces
         .as_ref()
@@ -246,6 +252,34 @@ pub async fn update_shared_workspace_member(
     Ok(Json(serde_json::json!({ "updated": true })))
 }
 
+/// Set which capabilities (chat, terminal, files) a member holds.
+///
+/// A null `permissions` resets the member to the defaults of their role.
+pub async fn update_shared_workspace_member_permissions(
+    State(state): State<AppState>,
+    user: CurrentUser,
+    Path((workspace_id, target_user_id)): Path<(String, String)>,
+    Json(request): Json<UpdateMemberPermissionsRequest>,
+) -> ApiResult<Json<SharePermissions>> {
+    refuse_impersonated(&user, "Member permissions cannot be changed")?;
+    let service = state
+        .shared_workspaces
+        .as_ref()
+        .ok_or_else(|| ApiError::internal("shared workspaces not configured"))?;
+
+    let permissions = service
+        .update_member_permissions(
+            &workspace_id,
+            user.id(),
+            &target_user_id,
+            request.permissions,
+        )
+        .await
+        .map_err(|e| ApiError::bad_request(format!("{}", e)))?;
+
+    Ok(Json(permissions))
+}
+
 /// Remove a member from a shared workspace.
 pub async fn remove_shared_workspace_member(
     State(state): State<AppState>,
@@ -389,6 +423,9 @@ pub async fn convert_to_shared_workspace(
     user: CurrentUser,
     Json(mut request): Json<ConvertToSharedRequest>,
 ) -> ApiResult<Json<serde_json::Value>> {
+    if !request.member_ids.is_empty() {
+        refuse_impersonated(&user, "Members cannot be added")?;
+    }
     let service = state
         .shared_workspaces
         .as_ref()
@@ -433,6 +470,7 @@ pub async fn transfer_shared_workspace_ownership(
     Path(workspace_id): Path<String>,
     Json(request): Json<TransferOwnershipRequest>,
 ) -> ApiResult<Json<serde_json::Value>> {
+    refuse_impersonated(&user, "Ownership cannot be transferred")?;
     let service = state
         .shared_workspaces
         .as_ref()
@@ -0,0 +1,131 @@
+//! Public status page and incident note handlers.
+
+use std::net::IpAddr;
+
+use axum::{
+    Json,
+    extract::{Path, Query, State},
+    http::{Extensions, StatusCode},
+};
+use tracing::{info, instrument};
+
+use crate::auth::RequireAdmin;
+use crate::net;
+use crate::status::{
+    CreateIncidentRequest, Incident, IncidentListQuery, StatusReport, StatusService,
+    UpdateIncidentRequest,
+};
+
+use crate::api::error::{ApiError, ApiResult};
+use crate::api::state::AppState;
+
+fn status_service(state: &AppState) -> ApiResult<&StatusService> {
+    state
+        .status
+        .as_deref()
+        .ok_or_else(|| ApiError::not_found("Status page is disabled"))
+}
+
+/// Public service status: component health, uptime and active incidents.
+///
+/// GET /api/status (unauthenticated, rate limited per client IP)
+pub async fn status(
+    State(state): State<AppState>,
+    extensions: Extensions,
+) -> ApiResult<Json<StatusReport>> {
+    let service = status_service(&state)?;
+    let client_ip = net::client_ip(&extensions).unwrap_or(IpAddr::from([127, 0, 0, 1]));
+    if !service.check_rate_limit(client_ip) {
+        return Err(ApiError::too_many_requests(
+            "Status rate limit exceeded, retry in a minute",
+        ));
+    }
+
+    let report = service
+        .report()
+        .await
+        .map_err(|e| ApiError::internal(format!("Failed to build status report: {e}")))?;
+    Ok(Json(report))
+}
+
+/// List status incidents (admin only).
+#[instrument(skip(state, _user))]
+pub async fn admin_list_incidents(
+    State(state): State<AppState>,
+    RequireAdmin(_user): RequireAdmin,
+    Query(query): Query<IncidentListQuery>,
+) -> ApiResult<Json<Vec<Incident>>> {
+    let incidents = status_service(&state)?
+        .repository()
+        .list_incidents(&query)
+        .await?;
+    Ok(Json(incidents))
+}
+
+/// Post an incident note to the status page (admin only).
+#[instrument(skip(state, user, request))]
+pub async fn admin_create_incident(
+    State(state): State<AppState>,
+    RequireAdmin(user): RequireAdmin,
+    Json(mut request): Json<CreateIncidentRequest>,
+) -> ApiResult<(StatusCode, Json<Incident>)> {
+    request.title = request.title.trim().to_string();
+    if request.title.is_empty() {
+        return Err(ApiError::bad_request("Incident title cannot be empty"));
+    }
+    request.component = request.component.filter(|c| !c.trim().is_empty());
+
+    let service = status_service(&state)?;
+    let incident = service
+        .repository()
+        .create_incident(&request, user.id())
+        .await?;
+    service.invalidate().await;
+    info!(incident_id = %incident.id, severity = ?incident.severity, "Created status incident");
+    Ok((StatusCode::CREATED, Json(incident)))
+}
+
+/// Update or resolve an incident note (admin only).
+#[instrument(skip(state, _user, request))]
+pub async fn admin_update_incident(
+    State(state): State<AppState>,
+    RequireAdmin(_user): RequireAdmin,
+    Path(incident_id): Path<String>,
+    Json(request): Json<UpdateIncidentRequest>,
+) -> ApiResult<Json<Incident>> {
+    if request
+        .title
+        .as_deref()
+        .is_some_and(|title| title.trim().is_empty())
+    {
+        return Err(ApiError::bad_request("Incident title cannot be empty"));
+    }
+
+    let service = status_service(&state)?;
+    let incident = service
+        .repository()
+        .update_incident(&incident_id, &request)
+        .await?
+        .ok_or_else(|| ApiError::not_found(format!("Incident {incident_id} not found")))?;
+    service.invalidate().await;
+    info!(incident_id = %incident.id, resolved = incident.resolved_at.is_some(), "Updated status incident");
+    Ok(Json(incident))
+}
+
+/// Delete an incident note (admin only).
+#[instrument(skip(state, _user))]
+pub async fn admin_delete_incident(
+    State(state): State<AppState>,
+    RequireAdmin(_user): RequireAdmin,
+    Path(incident_id): Path<String>,
+) -> ApiResult<StatusCode> {
+    let service = status_service(&state)?;
+    if !service.repository().delete_incident(&incident_id).await? {
+        return Err(ApiError::not_found(format!(
+            "Incident {incident_id} not found"
+        )));
+    }
+    service.invalidate().await;
+    info!(incident_id = %incident_id, "Deleted status incident");
+    Ok(StatusCode::NO_CONTENT)
+}
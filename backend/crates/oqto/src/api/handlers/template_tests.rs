@@ -0,0 +1,235 @@
+//! Template testing sandbox.
+//!
+//! `POST /templates/{name}/test` creates a throwaway project from a template
+//! in the caller's workspace, runs the template's declared smoke check there
+//! through the runner, deletes the project again and records the result
+//! against the template's current version. `GET /templates/{name}/tests`
+//! lists the recorded runs.
+
+use std::path::{Path as FsPath, PathBuf};
+use std::time::Instant;
+
+use axum::{
+    Json,
+    extract::{Path, Query, State},
+};
+use serde::Serialize;
+use tracing::{info, instrument, warn};
+
+use oqto_runner::protocol::{SmokeCheckRequest, SmokeCheckResponse};
+
+use crate::auth::{Access, Permission};
+use crate::projects;
+use crate::template_tests::{
+    self, NewTemplateTestRun, SmokeCheck, TemplateTestListQuery, TemplateTestRepository,
+    TemplateTestRun, TemplateTestStatus,
+};
+
+use super::projects::{create_from_template, stage_template};
+use super::trx::validated_runner;
+use crate::api::error::{ApiError, ApiResult};
+use crate::api::state::AppState;
+
+/// Response of `GET /templates/{name}/tests`.
+#[derive(Debug, Serialize)]
+pub struct TemplateTestsResponse {
+    /// Version of the template as it is now.
+    pub version: String,
+    pub runs: Vec<TemplateTestRun>,
+}
+
+fn test_runs(state: &AppState) -> ApiResult<&TemplateTestRepository> {
+    state
+        .template_tests
+        .as_deref()
+        .ok_or_else(|| ApiError::service_unavailable("Template tests are not available"))
+}
+
+/// Directory of template `name`; it must be a top-level entry of the
+/// templates repository, as listed by `GET /projects/templates`.
+fn template_dir(state: &AppState, name: &str) -> ApiResult<(PathBuf, PathBuf)> {
+    let repo_path = state
+        .templates
+        .repo_path
+        .clone()
+        .ok_or_else(|| ApiError::not_found("No templates repository configured"))?;
+    if name.is_empty() || name.starts_with('.') || name.contains(['/', '\\']) {
+        return Err(ApiError::bad_request("invalid template name"));
+    }
+    let dir = repo_path.join(name);
+    if !dir.is_dir() {
+        return Err(ApiError::not_found(format!("Template '{name}' not found")));
+    }
+    Ok((repo_path, dir))
+}
+
+fn status_of(result: &SmokeCheckResponse) -> TemplateTestStatus {
+    if result.timed_out {
+        TemplateTestStatus::TimedOut
+    } else if result.exit_code == Some(0) {
+        TemplateTestStatus::Passed
+    } else {
+        TemplateTestStatus::Failed
+    }
+}
+
+/// Create the throwaway project, run the check in it and delete it again.
+async fn run_in_sandbox(
+    state: &AppState,
+    user_id: &str,
+    name: &str,
+    template_dir: &FsPath,
+    check: &SmokeCheck,
+) -> ApiResult<SmokeCheckResponse> {
+    let workspace_root = state.sessions.for_user(user_id).workspace_root();
+    let sandbox = workspace_root
+        .join(template_tests::SANDBOX_DIR)
+        .join(format!("{name}-{}", nanoid::nanoid!(10)));
+    // Variables get stand-in values; nobody is there to ask.
+    let variables = projects::read_variables(template_dir)
+        .map_err(|e| ApiError::bad_request(format!("{e:#}")))?;
+    let staged = stage_template(template_dir, &projects::sample_values(&variables))?;
+    let source_dir = staged
+        .as_ref()
+        .map(|dir| dir.path().join("template"))
+        .unwrap_or_else(|| template_dir.to_path_buf());
+    create_from_template(state, user_id, None, &workspace_root, &source_dir, &sandbox)?;
+    drop(staged);
+
+    let (canonical, runner) = match validated_runner(state, user_id, &sandbox.to_string_lossy())
+        .await
+    {
+        Ok(resolved) => resolved,
+        Err(err) => {
+            // Without a runner, clean up directly where the backend can.
+            if let Err(e) = tokio::fs::remove_dir_all(&sandbox).await {
+                warn!(sandbox = %sandbox.display(), "Failed to delete template test project: {e}");
+            }
+            return Err(err);
+        }
+    };
+    let result = runner
+        .run_smoke_check(SmokeCheckRequest {
+            workspace_path: canonical.clone(),
+            command: check.command.clone(),
+            timeout_secs: check.timeout_secs,
+        })
+        .await
+        .map_err(|e| ApiError::bad_gateway(format!("Smoke check failed to run: {e:#}")));
+    if let Err(e) = runner.delete_path(canonical, true).await {
+        warn!(sandbox = %sandbox.display(), "Failed to delete template test project: {e:#}");
+    }
+    result
+}
+
+/// Run a template's smoke check in a throwaway project.
+#[instrument(skip(state, access))]
+pub async fn test_template(
+    State(state): State<AppState>,
+    access: Access,
+    Path(name): Path<String>,
+) -> ApiResult<Json<TemplateTestRun>> {
+    let user = access.require(Permission::TemplatesManage)?;
+    let repo = test_runs(&state)?;
+    let (repo_path, dir) = template_dir(&state, &name)?;
+    let check = template_tests::read_smoke_check(&dir)
+        .map_err(|e| ApiError::bad_request(format!("{e:#}")))?
+        .ok_or_else(|| {
+            ApiError::bad_request(format!(
+                "Template '{name}' declares no smoke_check in template.json"
+            ))
+        })?;
+    let version = template_tests::template_version(&dir)
+        .map_err(|e| ApiError::internal(format!("Failed to hash template: {e:#}")))?;
+    let commit = template_tests::template_commit(&repo_path, &name).await;
+
+    let started = Instant::now();
+    let outcome = run_in_sandbox(&state, user.id(), &name, &dir, &check).await;
+    let (status, exit_code, output, output_truncated, duration_ms) = match outcome {
+        Ok(result) => (
+            status_of(&result),
+            result.exit_code.map(i64::from),
+            result.output,
+            result.output_truncated,
+            result.duration_ms as i64,
+        ),
+        Err(err) => (
+            TemplateTestStatus::Error,
+            None,
+            err.to_string(),
+            false,
+            started.elapsed().as_millis() as i64,
+        ),
+    };
+
+    let run = repo
+        .create(NewTemplateTestRun {
+            template: &name,
+            version: &version,
+            commit: commit.as_deref(),
+            command: &check.command,
+            status,
+            exit_code,
+            output: &output,
+            output_truncated,
+            duration_ms,
+            started_by: user.id(),
+        })
+        .await?;
+    info!(
+        user_id = %user.id(),
+        template = %name,
+        version = %version,
+        status = status.as_str(),
+        duration_ms,
+        "Template test finished"
+    );
+    Ok(Json(run))
+}
+
+/// Recorded test runs of a template, newest first.
+#[instrument(skip(state, access))]
+pub async fn list_template_tests(
+    State(state): State<AppState>,
+    access: Access,
+    Path(name): Path<String>,
+    Query(query): Query<TemplateTestListQuery>,
+) -> ApiResult<Json<TemplateTestsResponse>> {
+    access.require(Permission::TemplatesManage)?;
+    let repo = test_runs(&state)?;
+    let (_, dir) = template_dir(&state, &name)?;
+    let version = template_tests::template_version(&dir)
+        .map_err(|e| ApiError::internal(format!("Failed to hash template: {e:#}")))?;
+    let filter = match query.version.as_deref() {
+        Some("current") => Some(version.as_str()),
+        other => other,
+    };
+    let runs = repo.list(&name, filter, query.limit).await?;
+    Ok(Json(TemplateTestsResponse { version, runs }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn check_results_map_to_statuses() {
+        let result = |exit_code, timed_out| SmokeCheckResponse {
+            exit_code,
+            timed_out,
+            output: String::new(),
+            output_truncated: false,
+            duration_ms: 10,
+        };
+        assert_eq!(
+            status_of(&result(Some(0), false)),
+            TemplateTestStatus::Passed
+        );
+        assert_eq!(
+            status_of(&result(Some(2), false)),
+            TemplateTestStatus::Failed
+        );
+        assert_eq!(status_of(&result(None, false)), TemplateTestStatus::Failed);
+        assert_eq!(status_of(&result(None, true)), TemplateTestStatus::TimedOut);
+    }
+}
@@ -0,0 +1,493 @@
+//! Inbound trigger handlers and the mailbox poller.
+//!
+//! Users define triggers under `/api/me/triggers`. External services fire
+//! them with `POST /api/triggers/{token}` (no session auth: the token is the
+//! credential), and emails to a trigger's subaddress fire them when the IMAP
+//! poller is configured. Each event starts a background run like an agent
+//! schedule run; its history is at `GET /api/me/triggers/{name}/runs`.
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use axum::{
+    Json,
+    body::Bytes,
+    extract::{Path, Query, State},
+    http::{Extensions, HeaderMap, StatusCode, header},
+};
+use serde::{Deserialize, Serialize};
+use tracing::{debug, info, instrument, warn};
+
+use crate::auth::CurrentUser;
+use crate::net;
+use crate::triggers::{
+    AgentTrigger, ParsedMail, SaveTriggerRequest, TriggerEvent, TriggerResult, TriggerRun,
+    TriggerRunStatus, TriggerService, TriggerSource, TriggerWithToken, generate_token, hash_token,
+    render_prompt, sender_allowed, token_of_address, valid_sender_entry,
+};
+
+use super::impersonation::refuse_impersonated;
+use super::schedules::{AgentRunSpec, checked_workspace_path, execute_agent_run, validate_name};
+use crate::api::error::{ApiError, ApiResult};
+use crate::api::state::AppState;
+
+/// Most entries in a trigger's sender allowlist.
+const MAX_ALLOWED_SENDERS: usize = 100;
+
+/// Shortest mailbox poll interval honoured.
+const MIN_POLL_INTERVAL_SECS: u64 = 10;
+
+fn triggers(state: &AppState) -> ApiResult<&Arc<TriggerService>> {
+    state
+        .triggers
+        .as_ref()
+        .ok_or_else(|| ApiError::service_unavailable("Triggers are disabled"))
+}
+
+async fn owned_trigger(
+    service: &TriggerService,
+    user_id: &str,
+    name: &str,
+) -> ApiResult<AgentTrigger> {
+    service
+        .repo()
+        .get(user_id, name)
+        .await?
+        .ok_or_else(|| ApiError::not_found(format!("Trigger '{name}' not found")))
+}
+
+fn with_token(trigger: AgentTrigger, token: String) -> TriggerWithToken {
+    TriggerWithToken {
+        url: format!("/api/triggers/{token}"),
+        trigger,
+        token,
+    }
+}
+
+/// Validate a definition and resolve its workspace path.
+async fn checked(
+    state: &AppState,
+    user_id: &str,
+    name: &str,
+    mut request: SaveTriggerRequest,
+) -> ApiResult<SaveTriggerRequest> {
+    validate_name(name)?;
+    if request.prompt.trim().is_empty() && request.template.is_none() {
+        return Err(ApiError::bad_request("prompt must not be empty"));
+    }
+    request.template = request
+        .template
+        .as_deref()
+        .map(|t| t.trim().trim_start_matches('/').to_string());
+    if request.template.as_deref() == Some("") {
+        return Err(ApiError::bad_request("template must not be empty"));
+    }
+    if request.timeout_secs.is_some_and(|t| t < 1) {
+        return Err(ApiError::bad_request("timeout_secs must be at least 1"));
+    }
+
+    request.allowed_senders = request
+        .allowed_senders
+        .iter()
+        .map(|entry| entry.trim().to_string())
+        .filter(|entry| !entry.is_empty())
+        .collect();
+    if request.allowed_senders.len() > MAX_ALLOWED_SENDERS {
+        return Err(ApiError::bad_request(format!(
+            "allowed_senders takes at most {MAX_ALLOWED_SENDERS} entries"
+        )));
+    }
+    if let Some(entry) = request
+        .allowed_senders
+        .iter()
+        .find(|entry| !valid_sender_entry(entry))
+    {
+        return Err(ApiError::bad_request(format!(
+            "'{entry}' is not an email address, @domain or IP address"
+        )));
+    }
+
+    request.reply_webhook_url = request
+        .reply_webhook_url
+        .map(|url| url.trim().to_string())
+        .filter(|url| !url.is_empty());
+    if let Some(url) = &request.reply_webhook_url {
+        let valid = reqwest::Url::parse(url)
+            .is_ok_and(|u| matches!(u.scheme(), "http" | "https") && u.host().is_some());
+        if !valid {
+            return Err(ApiError::bad_request(
+                "reply_webhook_url must be an http(s) URL",
+            ));
+        }
+    }
+
+    request.workspace_path =
+        checked_workspace_path(state, user_id, &request.workspace_path).await?;
+    Ok(request)
+}
+
+/// The caller's triggers.
+#[instrument(skip(state, user))]
+pub async fn list_triggers(
+    State(state): State<AppState>,
+    user: CurrentUser,
+) -> ApiResult<Json<Vec<AgentTrigger>>> {
+    Ok(Json(triggers(&state)?.repo().list(user.id()).await?))
+}
+
+/// Define a trigger. The response carries its token, which is not shown
+/// again.
+#[instrument(skip(state, user, request))]
+pub async fn create_trigger(
+    State(state): State<AppState>,
+    user: CurrentUser,
+    Json(request): Json<SaveTriggerRequest>,
+) -> ApiResult<(StatusCode, Json<TriggerWithToken>)> {
+    // The token starts runs as the user without signing in.
+    refuse_impersonated(&user, "Triggers cannot be created")?;
+    let service = triggers(&state)?;
+    let name = request
+        .name
+        .as_deref()
+        .unwrap_or_default()
+        .trim()
+        .to_string();
+    let request = checked(&state, user.id(), &name, request).await?;
+    let repo = service.repo();
+    if repo.get(user.id(), &name).await?.is_some() {
+        return Err(ApiError::conflict(format!(
+            "A trigger named '{name}' already exists"
+        )));
+    }
+    if repo.count(user.id()).await? >= service.config().max_triggers_per_user {
+        return Err(ApiError::too_many_requests("Too many triggers"));
+    }
+
+    let (token, prefix) = generate_token();
+    let created = repo
+        .create(user.id(), &name, &request, &hash_token(&token), &prefix)
+        .await?;
+    info!(user_id = %user.id(), trigger = %name, "trigger created");
+    Ok((StatusCode::CREATED, Json(with_token(created, token))))
+}
+
+#[instrument(skip(state, user))]
+pub async fn get_trigger(
+    State(state): State<AppState>,
+    user: CurrentUser,
+    Path(name): Path<String>,
+) -> ApiResult<Json<AgentTrigger>> {
+    Ok(Json(
+        owned_trigger(triggers(&state)?, user.id(), &name).await?,
+    ))
+}
+
+/// Replace a trigger's definition. Its token stays valid.
+#[instrument(skip(state, user, request))]
+pub async fn update_trigger(
+    State(state): State<AppState>,
+    user: CurrentUser,
+    Path(name): Path<String>,
+    Json(request): Json<SaveTriggerRequest>,
+) -> ApiResult<Json<AgentTrigger>> {
+    let service = triggers(&state)?;
+    owned_trigger(service, user.id(), &name).await?;
+    let request = checked(&state, user.id(), &name, request).await?;
+    service
+        .repo()
+        .update(user.id(), &name, &request)
+        .await?
+        .map(Json)
+        .ok_or_else(|| ApiError::not_found(format!("Trigger '{name}' not found")))
+}
+
+/// Delete a trigger and its run history. Runs in progress finish.
+#[instrument(skip(state, user))]
+pub async fn delete_trigger(
+    State(state): State<AppState>,
+    user: CurrentUser,
+    Path(name): Path<String>,
+) -> ApiResult<StatusCode> {
+    if !triggers(&state)?.repo().delete(user.id(), &name).await? {
+        return Err(ApiError::not_found(format!("Trigger '{name}' not found")));
+    }
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Issue a new token for a trigger; the old one stops working.
+#[instrument(skip(state, user))]
+pub async fn rotate_trigger_token(
+    State(state): State<AppState>,
+    user: CurrentUser,
+    Path(name): Path<String>,
+) -> ApiResult<Json<TriggerWithToken>> {
+    refuse_impersonated(&user, "Trigger tokens cannot be rotated")?;
+    let (token, prefix) = generate_token();
+    let trigger = triggers(&state)?
+        .repo()
+        .rotate_token(user.id(), &name, &hash_token(&token), &prefix)
+        .await?
+        .ok_or_else(|| ApiError::not_found(format!("Trigger '{name}' not found")))?;
+    info!(user_id = %user.id(), trigger = %name, "trigger token rotated");
+    Ok(Json(with_token(trigger, token)))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct TriggerRunsQuery {
+    #[serde(default)]
+    pub limit: Option<i64>,
+}
+
+/// A trigger's runs, newest first.
+#[instrument(skip(state, user))]
+pub async fn list_trigger_runs(
+    State(state): State<AppState>,
+    user: CurrentUser,
+    Path(name): Path<String>,
+    Query(query): Query<TriggerRunsQuery>,
+) -> ApiResult<Json<Vec<TriggerRun>>> {
+    let service = triggers(&state)?;
+    let trigger = owned_trigger(service, user.id(), &name).await?;
+    Ok(Json(
+        service
+            .repo()
+            .list_runs(&trigger.id, query.limit.unwrap_or(50))
+            .await?,
+    ))
+}
+
+#[derive(Debug, Serialize)]
+pub struct TriggerAccepted {
+    pub run_id: String,
+    pub session_id: String,
+    pub status: TriggerRunStatus,
+}
+
+/// Fire a trigger with the request body as the event. JSON bodies are
+/// available to the prompt as `{{payload}}` and `{{payload.<field>}}`, a
+/// string `subject` field as `{{subject}}`; other bodies as `{{body}}`.
+///
+/// POST /api/triggers/{token} (unauthenticated, rate limited per client IP)
+pub async fn fire_trigger(
+    State(state): State<AppState>,
+    Path(token): Path<String>,
+    extensions: Extensions,
+    headers: HeaderMap,
+    body: Bytes,
+) -> ApiResult<(StatusCode, Json<TriggerAccepted>)> {
+    let service = triggers(&state)?;
+    let trigger = service
+        .repo()
+        .get_by_token_hash(&hash_token(&token))
+        .await?
+        .ok_or_else(|| ApiError::not_found("Trigger not found"))?;
+    if body.len() > service.config().max_payload_bytes {
+        return Err(ApiError::bad_request("Payload too large"));
+    }
+
+    let is_json = headers
+        .get(header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| v.starts_with("application/json"));
+    let payload = if is_json {
+        Some(
+            serde_json::from_slice::<serde_json::Value>(&body)
+                .map_err(|e| ApiError::bad_request(format!("Invalid JSON: {e}")))?,
+        )
+    } else {
+        None
+    };
+    let event = TriggerEvent {
+        sender: net::client_ip(&extensions).map(|ip| ip.to_string()),
+        subject: payload
+            .as_ref()
+            .and_then(|p| p.get("subject"))
+            .and_then(|s| s.as_str())
+            .map(str::to_string),
+        body: String::from_utf8_lossy(&body).into_owned(),
+        payload,
+        message_id: None,
+    };
+    if !sender_allowed(
+        &trigger.allowed_senders,
+        TriggerSource::Webhook,
+        event.sender.as_deref(),
+    ) {
+        return Err(ApiError::forbidden("Sender is not allowed"));
+    }
+
+    let run = start_trigger_run(&state, service, trigger, TriggerSource::Webhook, event).await?;
+    Ok((
+        StatusCode::ACCEPTED,
+        Json(TriggerAccepted {
+            run_id: run.id,
+            session_id: run.session_id,
+            status: run.status,
+        }),
+    ))
+}
+
+/// Record a run of `trigger` for `event` and start it in the background.
+async fn start_trigger_run(
+    state: &AppState,
+    service: &Arc<TriggerService>,
+    trigger: AgentTrigger,
+    source: TriggerSource,
+    event: TriggerEvent,
+) -> ApiResult<TriggerRun> {
+    if !trigger.enabled {
+        return Err(ApiError::forbidden("Trigger is disabled"));
+    }
+    let repo = service.repo();
+    if repo.count_active_runs(&trigger.id).await? >= service.config().max_active_runs_per_trigger {
+        return Err(ApiError::too_many_requests(
+            "Too many runs of this trigger in progress",
+        ));
+    }
+    let session_id = uuid::Uuid::new_v4().to_string();
+    let run = repo
+        .create_run(&trigger.id, source, &event, &session_id)
+        .await?;
+    info!(
+        user_id = %trigger.user_id,
+        trigger = %trigger.name,
+        run_id = %run.id,
+        source = ?source,
+        "Trigger fired"
+    );
+
+    let state = state.clone();
+    let service = Arc::clone(service);
+    let run_id = run.id.clone();
+    tokio::spawn(async move {
+        let _permit = match &state.priority_lanes {
+            Some(lanes) => lanes.acquire_background(&trigger.name).await,
+            None => None,
+        };
+        if let Err(e) = service.repo().mark_running(&run_id).await {
+            warn!("Failed to record trigger run start: {e:#}");
+        }
+        let prompt = render_prompt(&trigger.prompt, &event);
+        let spec = AgentRunSpec {
+            user_id: &trigger.user_id,
+            workspace_path: &trigger.workspace_path,
+            prompt: &prompt,
+            template: trigger.template.as_deref(),
+            harness: trigger.harness.as_deref(),
+            provider: trigger.provider.as_deref(),
+            model: trigger.model.as_deref(),
+            timeout: Duration::from_secs(
+                trigger
+                    .timeout_secs
+                    .map_or(service.config().run_timeout_secs, |t| t.max(1) as u64),
+            ),
+        };
+        let result = execute_agent_run(&state, &spec, &session_id).await;
+        let (status, answer, error) = match result {
+            Ok(answer) => (TriggerRunStatus::Succeeded, answer, None),
+            Err(e) => {
+                warn!(
+                    user_id = %trigger.user_id,
+                    trigger = %trigger.name,
+                    "Trigger run failed: {e:#}"
+                );
+                (TriggerRunStatus::Failed, None, Some(format!("{e:#}")))
+            }
+        };
+
+        let delivery = service
+            .deliver(
+                &trigger,
+                &event,
+                source,
+                &TriggerResult {
+                    trigger: &trigger.name,
+                    run_id: &run_id,
+                    session_id: &session_id,
+                    status,
+                    result: answer.as_deref(),
+                    error: error.as_deref(),
+                },
+            )
+            .await;
+        if let Err(e) = service
+            .repo()
+            .finish_run(&run_id, status, error.as_deref(), delivery.as_deref())
+            .await
+        {
+            warn!("Failed to record trigger run: {e:#}");
+        }
+    });
+    Ok(run)
+}
+
+/// Poll the configured mailbox every `poll_interval_secs` and fire the
+/// triggers emails are addressed to.
+pub async fn run_trigger_mail_poller(state: AppState) {
+    let Some(service) = state.triggers.clone() else {
+        return;
+    };
+    if !service.config().imap.enabled {
+        return;
+    }
+    let mut interval = tokio::time::interval(Duration::from_secs(
+        service
+            .config()
+            .imap
+            .poll_interval_secs
+            .max(MIN_POLL_INTERVAL_SECS),
+    ));
+    interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+    loop {
+        interval.tick().await;
+        match service.poll_mailbox().await {
+            Ok(mails) => {
+                for mail in mails {
+                    handle_mail(&state, &service, mail).await;
+                }
+            }
+            Err(e) => warn!("Failed to poll trigger mailbox: {e:#}"),
+        }
+    }
+}
+
+async fn handle_mail(state: &AppState, service: &Arc<TriggerService>, mail: ParsedMail) {
+    let Some(token) = mail.recipients.iter().find_map(|r| token_of_address(r)) else {
+        debug!(subject = ?mail.subject, "Ignoring email not addressed to a trigger");
+        return;
+    };
+    let trigger = match service.repo().get_by_token_hash(&hash_token(token)).await {
+        Ok(Some(trigger)) => trigger,
+        Ok(None) => {
+            warn!(from = ?mail.from, "Ignoring email to an unknown trigger");
+            return;
+        }
+        Err(e) => {
+            warn!("Failed to look up trigger for email: {e:#}");
+            return;
+        }
+    };
+    let event = TriggerEvent {
+        sender: mail.from,
+        subject: mail.subject,
+        body: mail.body,
+        payload: None,
+        message_id: mail.message_id,
+    };
+    if !sender_allowed(
+        &trigger.allowed_senders,
+        TriggerSource::Email,
+        event.sender.as_deref(),
+    ) {
+        warn!(
+            trigger = %trigger.name,
+            from = ?event.sender,
+            "Ignoring email from a sender that is not allowed"
+        );
+        return;
+    }
+    let name = trigger.name.clone();
+    if let Err(e) = start_trigger_run(state, service, trigger, TriggerSource::Email, event).await {
+        warn!(trigger = %name, "Failed to start trigger run for email: {e}");
+    }
+}
@@ -157,7 +157,7 @@ pub async fn validate_workspace_path(
 }
 
 /// Validate the workspace path and resolve the runner that owns it.
-async fn validated_runner(
+pub(crate) async fn validated_runner(
     state: &AppState,
     user_id: &str,
     workspace_path: &str,
@@ -0,0 +1,344 @@
+//! User environment export and import handlers.
+
+use std::collections::HashSet;
+
+use axum::{
+    Json,
+    body::{Body, Bytes},
+    extract::{Path, Query, State},
+    http::header,
+    response::{IntoResponse, Response},
+};
+use serde_json::{Map, Value};
+use tokio_util::io::ReaderStream;
+use tracing::{info, instrument};
+
+use crate::auth::{CurrentUser, RequireAdmin};
+use crate::macros::SaveMacroRequest;
+use crate::user::UpdateUserRequest;
+use crate::user_env::{
+    self, ConflictPolicy, EnvironmentArchive, EnvironmentManifest, EnvironmentSource, ExportQuery,
+    ImportAction, ImportQuery, ImportReport, ImportedItem, UserEnvConfig, WorkspaceTarget,
+};
+
+use crate::api::error::{ApiError, ApiResult};
+use crate::api::state::AppState;
+
+fn config(state: &AppState) -> ApiResult<&UserEnvConfig> {
+    if !state.user_env.enabled {
+        return Err(ApiError::not_found("Environment export is disabled"));
+    }
+    Ok(&state.user_env)
+}
+
+/// Download the caller's environment: settings, macros and workspaces with
+/// their metadata and memories, plus the workspace files with
+/// `include_data=true`.
+///
+/// GET /api/me/environment/export
+#[instrument(skip(state, user))]
+pub async fn export_my_environment(
+    State(state): State<AppState>,
+    user: CurrentUser,
+    Query(query): Query<ExportQuery>,
+) -> ApiResult<Response> {
+    export_environment(&state, user.id(), query.include_data).await
+}
+
+/// Apply an environment archive (the request body) to the caller.
+///
+/// POST /api/me/environment/import
+#[instrument(skip(state, user, body))]
+pub async fn import_my_environment(
+    State(state): State<AppState>,
+    user: CurrentUser,
+    Query(query): Query<ImportQuery>,
+    body: Bytes,
+) -> ApiResult<Json<ImportReport>> {
+    import_environment(&state, user.id(), query, body)
+        .await
+        .map(Json)
+}
+
+/// Download a user's environment (admin only).
+///
+/// GET /api/admin/users/{user_id}/environment/export
+#[instrument(skip(state, _admin))]
+pub async fn admin_export_user_environment(
+    State(state): State<AppState>,
+    RequireAdmin(_admin): RequireAdmin,
+    Path(user_id): Path<String>,
+    Query(query): Query<ExportQuery>,
+) -> ApiResult<Response> {
+    export_environment(&state, &user_id, query.include_data).await
+}
+
+/// Apply an environment archive to a user (admin only).
+///
+/// POST /api/admin/users/{user_id}/environment/import
+#[instrument(skip(state, _admin, body))]
+pub async fn admin_import_user_environment(
+    State(state): State<AppState>,
+    RequireAdmin(_admin): RequireAdmin,
+    Path(user_id): Path<String>,
+    Query(query): Query<ImportQuery>,
+    body: Bytes,
+) -> ApiResult<Json<ImportReport>> {
+    import_environment(&state, &user_id, query, body)
+        .await
+        .map(Json)
+}
+
+async fn export_environment(
+    state: &AppState,
+    user_id: &str,
+    include_data: bool,
+) -> ApiResult<Response> {
+    let config = config(state)?.clone();
+    let user = state
+        .users
+        .get_user(user_id)
+        .await?
+        .ok_or_else(|| ApiError::not_found(format!("User {user_id} not found")))?;
+
+    let settings = parse_settings(user.settings.as_deref());
+    let macros = match &state.macros {
+        Some(service) => service
+            .repo()
+            .list_for_user(user_id)
+            .await?
+            .into_iter()
+            .map(|m| SaveMacroRequest {
+                name: m.name,
+                description: m.description,
+                params: m.params,
+                steps: m.steps,
+            })
+            .collect(),
+        None => Vec::new(),
+    };
+
+    let root = state.sessions.for_user(user_id).workspace_root();
+    let collect_root = root.clone();
+    let collect_config = config.clone();
+    let workspaces = tokio::task::spawn_blocking(move || {
+        user_env::collect_workspaces(&collect_root, include_data, &collect_config)
+    })
+    .await
+    .map_err(|e| ApiError::internal(format!("export task failed: {e}")))?
+    .map_err(|e| ApiError::internal(format!("Failed to read workspaces: {e:#}")))?;
+
+    let data_bytes: u64 = workspaces.iter().map(|w| w.bytes).sum();
+    if data_bytes > config.max_data_bytes {
+        return Err(ApiError::bad_request(format!(
+            "Workspace files total {data_bytes} bytes, more than the {} allowed; \
+             export without data or exclude large directories in .oqtoignore",
+            config.max_data_bytes
+        )));
+    }
+
+    let manifest = EnvironmentManifest {
+        version: user_env::FORMAT_VERSION,
+        exported_at: chrono::Utc::now(),
+        source: EnvironmentSource {
+            username: user.username.clone(),
+            display_name: user.display_name.clone(),
+            oqto_version: env!("CARGO_PKG_VERSION").to_string(),
+        },
+        settings,
+        macros,
+        workspaces,
+    };
+    let workspace_count = manifest.workspaces.len();
+    let macro_count = manifest.macros.len();
+    let file_name = format!(
+        "oqto-env-{}-{}.zip",
+        user.username,
+        manifest.exported_at.format("%Y%m%d")
+    );
+    let (file, size) =
+        tokio::task::spawn_blocking(move || user_env::write_archive(&manifest, &root, &config))
+            .await
+            .map_err(|e| ApiError::internal(format!("export task failed: {e}")))?
+            .map_err(|e| ApiError::internal(format!("Failed to write archive: {e:#}")))?;
+
+    info!(
+        user_id = %user_id,
+        workspaces = workspace_count,
+        macros = macro_count,
+        include_data,
+        bytes = size,
+        "Exported user environment"
+    );
+    let body = Body::from_stream(ReaderStream::new(tokio::fs::File::from_std(file)));
+    Ok((
+        [
+            (header::CONTENT_TYPE, "application/zip".to_string()),
+            (
+                header::CONTENT_DISPOSITION,
+                format!("attachment; filename=\"{file_name}\""),
+            ),
+            (header::CONTENT_LENGTH, size.to_string()),
+        ],
+        body,
+    )
+        .into_response())
+}
+
+async fn import_environment(
+    state: &AppState,
+    user_id: &str,
+    query: ImportQuery,
+    body: Bytes,
+) -> ApiResult<ImportReport> {
+    let config = config(state)?;
+    let user = state
+        .users
+        .get_user(user_id)
+        .await?
+        .ok_or_else(|| ApiError::not_found(format!("User {user_id} not found")))?;
+    let mut archive = EnvironmentArchive::open(body, config)
+        .map_err(|e| ApiError::bad_request(format!("Invalid environment archive: {e:#}")))?;
+    let manifest = archive.manifest().clone();
+
+    // Settings
+    let mut settings = parse_settings(user.settings.as_deref());
+    let settings_outcome =
+        user_env::merge_settings(&mut settings, &manifest.settings, query.on_conflict);
+    let settings_changed =
+        !settings_outcome.added.is_empty() || !settings_outcome.replaced.is_empty();
+    if settings_changed && !query.dry_run {
+        state
+            .users
+            .update_user(
+                user_id,
+                UpdateUserRequest {
+                    settings: Some(Value::Object(settings).to_string()),
+                    ..Default::default()
+                },
+            )
+            .await?;
+    }
+
+    // Macros
+    let macros = match &state.macros {
+        Some(service) => import_macros(service, user_id, &manifest.macros, &query).await?,
+        None => manifest
+            .macros
+            .iter()
+            .map(|m| ImportedItem::failed(&m.name, "macros are not enabled on this server"))
+            .collect(),
+    };
+
+    // Workspaces
+    let target = WorkspaceTarget {
+        root: state.sessions.for_user(user_id).workspace_root(),
+        linux_username: state
+            .linux_users
+            .as_ref()
+            .filter(|lu| lu.enabled)
+            .map(|lu| {
+                user.linux_username
+                    .clone()
+                    .unwrap_or_else(|| lu.linux_username(user_id))
+            }),
+    };
+    let workspace_query = query.clone();
+    let workspaces = tokio::task::spawn_blocking(move || {
+        user_env::import_workspaces(&mut archive, &target, &workspace_query)
+    })
+    .await
+    .map_err(|e| ApiError::internal(format!("import task failed: {e}")))?;
+
+    info!(
+        user_id = %user_id,
+        source = %manifest.source.username,
+        dry_run = query.dry_run,
+        on_conflict = ?query.on_conflict,
+        workspaces = workspaces.len(),
+        macros = macros.len(),
+        "Imported user environment"
+    );
+    Ok(ImportReport {
+        dry_run: query.dry_run,
+        source: manifest.source,
+        exported_at: manifest.exported_at,
+        settings: settings_outcome,
+        macros,
+        workspaces,
+    })
+}
+
+/// The user's settings object; anything else counts as empty.
+fn parse_settings(settings: Option<&str>) -> Map<String, Value> {
+    settings
+        .and_then(|s| serde_json::from_str::<Map<String, Value>>(s).ok())
+        .unwrap_or_default()
+}
+
+async fn import_macros(
+    service: &crate::macros::MacroService,
+    user_id: &str,
+    incoming: &[SaveMacroRequest],
+    query: &ImportQuery,
+) -> ApiResult<Vec<ImportedItem>> {
+    let existing = service.repo().list_for_user(user_id).await?;
+    let mut taken: HashSet<String> = existing.iter().map(|m| m.name.clone()).collect();
+    let mut count = existing.len() as i64;
+    let mut items = Vec::with_capacity(incoming.len());
+
+    for request in incoming {
+        let mut request = request.clone();
+        request.name = request.name.trim().to_string();
+        if let Err(e) = crate::macros::validate(&request, service.config()) {
+            items.push(ImportedItem::failed(&request.name, e));
+            continue;
+        }
+        let name = request.name.clone();
+        let current = existing.iter().find(|m| m.name == name);
+
+        let mut item = match (current, query.on_conflict) {
+            (None, _) => ImportedItem::new(&name, ImportAction::Created),
+            (Some(_), ConflictPolicy::Skip) => {
+                items.push(ImportedItem::new(&name, ImportAction::Skipped));
+                continue;
+            }
+            (Some(_), ConflictPolicy::Overwrite) => {
+                ImportedItem::new(&name, ImportAction::Replaced)
+            }
+            (Some(_), ConflictPolicy::Rename) => {
+                request.name = user_env::free_name(&taken, |n| match n {
+                    1 => format!("{name} (imported)"),
+                    n => format!("{name} (imported {n})"),
+                });
+                let mut item = ImportedItem::new(&name, ImportAction::Renamed);
+                item.imported_as = Some(request.name.clone());
+                item
+            }
+        };
+        let replaces = match item.action {
+            ImportAction::Replaced => current,
+            _ => None,
+        };
+        if replaces.is_none() && count >= service.config().max_macros_per_user {
+            items.push(ImportedItem::failed(&name, "too many macros"));
+            continue;
+        }
+        taken.insert(request.name.clone());
+
+        if !query.dry_run {
+            let result = match replaces {
+                Some(current) => service.repo().update(&current.id, &request).await,
+                None => service.repo().create(user_id, &request).await.map(drop),
+            };
+            if let Err(e) = result {
+                item = ImportedItem::failed(&name, e);
+            }
+        }
+        if replaces.is_none() && item.action != ImportAction::Failed {
+            count += 1;
+        }
+        items.push(item);
+    }
+    Ok(items)
+}
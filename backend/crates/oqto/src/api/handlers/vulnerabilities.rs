@@ -0,0 +1,119 @@
+//! Dependency vulnerability scan handlers.
+
+use std::sync::Arc;
+
+use axum::{
+    Json,
+    extract::{Path, Query, State},
+    http::StatusCode,
+};
+use tracing::{debug, instrument};
+
+use crate::auth::CurrentUser;
+use crate::vuln_scan::{
+    ScanListQuery, StartScanRequest, VulnScanService, WorkspaceScan, WorkspaceVulnerabilities,
+};
+
+use super::trx::{validate_workspace_path, validated_runner};
+use crate::api::error::{ApiError, ApiResult};
+use crate::api::state::AppState;
+
+fn vuln_service(state: &AppState) -> ApiResult<&Arc<VulnScanService>> {
+    state
+        .vuln_scans
+        .as_ref()
+        .ok_or_else(|| ApiError::not_found("Vulnerability scans are disabled"))
+}
+
+/// List workspace scans with their severity counts.
+///
+/// With `workspace_path`, returns that workspace's scan (if any); otherwise
+/// the scans the caller last triggered.
+#[instrument(skip(state, user))]
+pub async fn list_workspace_vulnerability_scans(
+    State(state): State<AppState>,
+    user: CurrentUser,
+    Query(query): Query<ScanListQuery>,
+) -> ApiResult<Json<Vec<WorkspaceScan>>> {
+    let service = vuln_service(&state)?;
+    let scans = match query.workspace_path.as_deref() {
+        Some(workspace_path) => {
+            let canonical = validate_workspace_path(&state, user.id(), workspace_path).await?;
+            service
+                .repository()
+                .get_by_path(&canonical.display().to_string())
+                .await?
+                .into_iter()
+                .collect()
+        }
+        None => service.repository().list_for_user(user.id()).await?,
+    };
+    Ok(Json(scans))
+}
+
+/// Latest scan of a workspace and its findings, most severe first.
+///
+/// `{id}` is the scan id from the list endpoint; it stays the same across
+/// re-scans of the workspace.
+#[instrument(skip(state, user))]
+pub async fn get_workspace_vulnerabilities(
+    State(state): State<AppState>,
+    user: CurrentUser,
+    Path(id): Path<String>,
+) -> ApiResult<Json<WorkspaceVulnerabilities>> {
+    let service = vuln_service(&state)?;
+    let not_found = || ApiError::not_found(format!("Workspace scan {id} not found"));
+    let scan = service.repository().get(&id).await?.ok_or_else(not_found)?;
+    // Workspaces the caller cannot (or can no longer) access are reported
+    // as missing.
+    if validate_workspace_path(&state, user.id(), &scan.workspace_path)
+        .await
+        .is_err()
+    {
+        return Err(not_found());
+    }
+    let vulnerabilities = service.repository().vulnerabilities(&scan.id).await?;
+    Ok(Json(WorkspaceVulnerabilities {
+        scan,
+        vulnerabilities,
+    }))
+}
+
+/// Scan a workspace now. The scan runs in the background; poll
+/// `GET /api/workspaces/{id}/vulnerabilities` for the result.
+#[instrument(skip(state, user, request))]
+pub async fn scan_workspace_vulnerabilities(
+    State(state): State<AppState>,
+    user: CurrentUser,
+    Json(request): Json<StartScanRequest>,
+) -> ApiResult<(StatusCode, Json<WorkspaceScan>)> {
+    let service = vuln_service(&state)?;
+    let (canonical, runner) = validated_runner(&state, user.id(), &request.workspace_path).await?;
+    let scan = service
+        .scan_now(user.id(), canonical, runner)
+        .await?
+        .ok_or_else(|| ApiError::conflict("A scan of this workspace is already running"))?;
+    Ok((StatusCode::ACCEPTED, Json(scan)))
+}
+
+/// Schedule a scan after dependency files changed in a watched workspace.
+pub(crate) async fn schedule_after_dependency_change(
+    state: &AppState,
+    user_id: &str,
+    workspace_path: &str,
+) {
+    let Some(service) = state.vuln_scans.as_ref() else {
+        return;
+    };
+    if !service.config().scan_on_change {
+        return;
+    }
+    match validated_runner(state, user_id, workspace_path).await {
+        Ok((canonical, runner)) => service.schedule(user_id, canonical, runner),
+        Err(e) => debug!(
+            workspace_path = %workspace_path,
+            "Not scheduling vulnerability scan: {}",
+            e
+        ),
+    }
+}
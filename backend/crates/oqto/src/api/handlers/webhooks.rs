@@ -0,0 +1,133 @@
+//! Outbound webhook handlers: forwarding session lifecycle changes to the
+//! webhooks and the admin view of the delivery log.
+
+use axum::{
+    Json,
+    extract::{Path, Query, State},
+};
+use tokio::sync::broadcast::error::RecvError;
+use tracing::{info, instrument, warn};
+
+use crate::auth::RequireAdmin;
+use crate::session::{SessionLifecycle, SessionLifecycleEvent};
+use crate::webhooks::{
+    DeliveryListQuery, WebhookDelivery, WebhookEvent, WebhookInfo, WebhookService,
+};
+
+use crate::api::error::{ApiError, ApiResult};
+use crate::api::state::AppState;
+
+fn webhooks(state: &AppState) -> ApiResult<&WebhookService> {
+    state
+        .webhooks
+        .as_deref()
+        .ok_or_else(|| ApiError::not_found("No webhooks are configured"))
+}
+
+/// The webhook event and payload data of a lifecycle change.
+async fn lifecycle_payload(
+    state: &AppState,
+    event: SessionLifecycleEvent,
+) -> anyhow::Result<Option<(WebhookEvent, serde_json::Value)>> {
+    let Some(session) = state.sessions.get_session(&event.session_id).await? else {
+        return Ok(None);
+    };
+    let (webhook_event, error) = match event.lifecycle {
+        SessionLifecycle::Created => (WebhookEvent::SessionCreated, None),
+        SessionLifecycle::Started => (WebhookEvent::SessionStarted, None),
+        SessionLifecycle::Stopped => (WebhookEvent::SessionStopped, None),
+        SessionLifecycle::Failed { error } => (WebhookEvent::SessionFailed, Some(error)),
+    };
+    let mut data = serde_json::json!({
+        "session_id": session.id,
+        "readable_id": session.readable_id,
+        "user_id": session.user_id,
+        "workspace_path": session.workspace_path,
+        "agent": session.agent,
+        "runtime_mode": session.runtime_mode,
+        "status": session.status,
+    });
+    if let Some(error) = error {
+        data["error"] = serde_json::Value::String(error);
+    }
+    Ok(Some((webhook_event, data)))
+}
+
+/// Forward session lifecycle changes to the webhooks. Runs until shutdown.
+pub async fn run_session_webhooks(state: AppState) {
+    let Some(service) = state.webhooks.clone() else {
+        return;
+    };
+    let mut events = state.sessions.subscribe_lifecycle();
+    loop {
+        let event = match events.recv().await {
+            Ok(event) => event,
+            Err(RecvError::Lagged(skipped)) => {
+                warn!("Session webhooks: skipped {skipped} lifecycle events");
+                continue;
+            }
+            Err(RecvError::Closed) => return,
+        };
+        let session_id = event.session_id.clone();
+        match lifecycle_payload(&state, event).await {
+            Ok(Some((event, data))) => service.emit(event, data).await,
+            Ok(None) => {}
+            Err(e) => warn!(session_id = %session_id, "Failed to build session webhook: {e:#}"),
+        }
+    }
+}
+
+/// Configured webhooks, without their secrets (admin only).
+pub async fn admin_list_webhooks(
+    State(state): State<AppState>,
+    RequireAdmin(_user): RequireAdmin,
+) -> ApiResult<Json<Vec<WebhookInfo>>> {
+    Ok(Json(
+        state
+            .webhooks
+            .as_ref()
+            .map(|service| service.webhooks())
+            .unwrap_or_default(),
+    ))
+}
+
+/// Webhook delivery log, newest first (admin only).
+#[instrument(skip(state, _user))]
+pub async fn admin_list_webhook_deliveries(
+    State(state): State<AppState>,
+    RequireAdmin(_user): RequireAdmin,
+    Query(query): Query<DeliveryListQuery>,
+) -> ApiResult<Json<Vec<WebhookDelivery>>> {
+    Ok(Json(webhooks(&state)?.repo().list(&query).await?))
+}
+
+/// Queue a failed delivery again with a fresh set of attempts (admin only).
+#[instrument(skip(state, user))]
+pub async fn admin_retry_webhook_delivery(
+    State(state): State<AppState>,
+    RequireAdmin(user): RequireAdmin,
+    Path(delivery_id): Path<String>,
+) -> ApiResult<Json<WebhookDelivery>> {
+    let service = webhooks(&state)?;
+    let not_found = || ApiError::not_found(format!("Webhook delivery {delivery_id} not found"));
+    let delivery = service
+        .repo()
+        .get(&delivery_id)
+        .await?
+        .ok_or_else(not_found)?;
+    if !service.retry(&delivery.id).await? {
+        return Err(ApiError::conflict("Only failed deliveries can be retried"));
+    }
+    info!(
+        user_id = %user.id(),
+        delivery_id = %delivery.id,
+        webhook = %delivery.webhook,
+        "Queued webhook delivery again"
+    );
+    let delivery = service
+        .repo()
+        .get(&delivery_id)
+        .await?
+        .ok_or_else(not_found)?;
+    Ok(Json(delivery))
+}
@@ -0,0 +1,238 @@
+//! Delegated workspace access handlers.
+
+use axum::{
+    Json,
+    extract::{Path, Query, State},
+    http::StatusCode,
+};
+use tracing::instrument;
+
+use crate::auth::CurrentUser;
+use crate::workspace_access::{
+    AccessMode, ApproveAccessRequest, CreateAccessRequest, GrantListQuery, GrantStatus, NewGrant,
+    WorkspaceAccessGrant, WorkspaceAccessService,
+};
+
+use super::projects::sanitize_relative_path;
+use crate::api::error::{ApiError, ApiResult};
+use crate::api::state::AppState;
+
+/// Longest accepted request reason.
+const MAX_REASON_LEN: usize = 500;
+
+fn access_service(state: &AppState) -> ApiResult<&WorkspaceAccessService> {
+    state
+        .workspace_access
+        .as_deref()
+        .ok_or_else(|| ApiError::not_found("Delegated workspace access is disabled"))
+}
+
+/// Load a grant the caller is a party to. Grants of other users are reported
+/// as missing rather than forbidden.
+async fn party_grant(
+    service: &WorkspaceAccessService,
+    grant_id: &str,
+    user_id: &str,
+) -> ApiResult<WorkspaceAccessGrant> {
+    service
+        .repository()
+        .get(grant_id)
+        .await?
+        .filter(|g| g.owner_id == user_id || g.grantee_id == user_id)
+        .ok_or_else(|| ApiError::not_found(format!("Grant {grant_id} not found")))
+}
+
+/// List grants the caller requested (`direction=outgoing`, default) or
+/// grants on the caller's workspace (`direction=incoming`).
+#[instrument(skip(state, user))]
+pub async fn list_workspace_access_grants(
+    State(state): State<AppState>,
+    user: CurrentUser,
+    Query(query): Query<GrantListQuery>,
+) -> ApiResult<Json<Vec<WorkspaceAccessGrant>>> {
+    let grants = access_service(&state)?
+        .repository()
+        .list_for_user(user.id(), &query)
+        .await?;
+    Ok(Json(grants))
+}
+
+/// Ask another user for access to a directory in their workspace.
+///
+/// Nothing is granted until the owner approves; the owner receives a
+/// notification carrying the grant.
+#[instrument(skip(state, user, request))]
+pub async fn request_workspace_access(
+    State(state): State<AppState>,
+    user: CurrentUser,
+    Json(request): Json<CreateAccessRequest>,
+) -> ApiResult<(StatusCode, Json<WorkspaceAccessGrant>)> {
+    let service = access_service(&state)?;
+    let config = service.config();
+
+    let owner_ref = request.owner.trim();
+    let owner = match state.users.get_user(owner_ref).await? {
+        Some(owner) => Some(owner),
+        None => state.users.get_user_by_username(owner_ref).await?,
+    }
+    .filter(|owner| owner.is_active)
+    .ok_or_else(|| ApiError::not_found(format!("User {owner_ref} not found")))?;
+    if owner.id == user.id() {
+        return Err(ApiError::bad_request(
+            "You already have access to your own workspace",
+        ));
+    }
+
+    let duration_minutes = request
+        .duration_minutes
+        .unwrap_or(config.default_duration_minutes);
+    if !(1..=config.max_duration_minutes).contains(&duration_minutes) {
+        return Err(ApiError::bad_request(format!(
+            "duration_minutes must be between 1 and {}",
+            config.max_duration_minutes
+        )));
+    }
+
+    let rel = sanitize_relative_path(&request.path)?;
+    if rel.as_os_str().is_empty()
+        || rel.components().any(|c| {
+            !matches!(c, std::path::Component::Normal(_))
+                || c.as_os_str().to_string_lossy().starts_with('.')
+        })
+    {
+        return Err(ApiError::bad_request(
+            "path must name a non-hidden directory inside the owner's workspace",
+        ));
+    }
+    let path = state
+        .sessions
+        .for_user(&owner.id)
+        .workspace_root()
+        .join(rel)
+        .to_string_lossy()
+        .into_owned();
+
+    let reason = request
+        .reason
+        .as_deref()
+        .map(str::trim)
+        .filter(|r| !r.is_empty());
+    if reason.is_some_and(|r| r.len() > MAX_REASON_LEN) {
+        return Err(ApiError::bad_request(format!(
+            "reason must be at most {MAX_REASON_LEN} characters"
+        )));
+    }
+
+    if service
+        .repository()
+        .find_open(&owner.id, user.id(), &path)
+        .await?
+        .is_some()
+    {
+        return Err(ApiError::conflict(
+            "A request for this directory is already pending or active",
+        ));
+    }
+
+    let grant = service
+        .request(
+            &NewGrant {
+                owner_id: &owner.id,
+                grantee_id: user.id(),
+                session_id: request.session_id.as_deref().filter(|s| !s.is_empty()),
+                path: &path,
+                mode: request.mode,
+                reason,
+                duration_minutes,
+            },
+            user.display_name(),
+        )
+        .await?;
+    Ok((StatusCode::CREATED, Json(grant)))
+}
+
+/// Approve a pending request on the caller's workspace.
+///
+/// The owner may shorten the duration or downgrade read-write to read-only,
+/// never widen what was asked for.
+#[instrument(skip(state, user, body))]
+pub async fn approve_workspace_access(
+    State(state): State<AppState>,
+    user: CurrentUser,
+    Path(grant_id): Path<String>,
+    body: Option<Json<ApproveAccessRequest>>,
+) -> ApiResult<Json<WorkspaceAccessGrant>> {
+    let service = access_service(&state)?;
+    let grant = party_grant(service, &grant_id, user.id()).await?;
+    if grant.owner_id != user.id() {
+        return Err(ApiError::forbidden(
+            "Only the workspace owner can approve access",
+        ));
+    }
+    if grant.status != GrantStatus::Pending {
+        return Err(ApiError::conflict(format!(
+            "Grant is {:?}, not pending",
+            grant.status
+        )));
+    }
+
+    let body = body.map(|Json(b)| b).unwrap_or_default();
+    let mode = body.mode.unwrap_or(grant.mode);
+    if mode == AccessMode::ReadWrite && grant.mode == AccessMode::ReadOnly {
+        return Err(ApiError::bad_request(
+            "Cannot approve read-write access for a read-only request",
+        ));
+    }
+    let duration_minutes = body.duration_minutes.unwrap_or(grant.duration_minutes);
+    if !(1..=grant.duration_minutes).contains(&duration_minutes) {
+        return Err(ApiError::bad_request(format!(
+            "duration_minutes must be between 1 and {}",
+            grant.duration_minutes
+        )));
+    }
+
+    let grant = service
+        .approve(&grant, mode, duration_minutes)
+        .await
+        .map_err(|e| ApiError::internal(format!("Failed to grant access: {e:#}")))?;
+    Ok(Json(grant))
+}
+
+/// Decline a pending request on the caller's workspace.
+#[instrument(skip(state, user))]
+pub async fn deny_workspace_access(
+    State(state): State<AppState>,
+    user: CurrentUser,
+    Path(grant_id): Path<String>,
+) -> ApiResult<StatusCode> {
+    let service = access_service(&state)?;
+    let grant = party_grant(service, &grant_id, user.id()).await?;
+    if grant.owner_id != user.id() {
+        return Err(ApiError::forbidden(
+            "Only the workspace owner can deny access",
+        ));
+    }
+    if grant.status != GrantStatus::Pending || !service.deny(&grant).await? {
+        return Err(ApiError::conflict("Grant is not pending"));
+    }
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Revoke an active grant or withdraw a pending request (either party).
+#[instrument(skip(state, user))]
+pub async fn revoke_workspace_access(
+    State(state): State<AppState>,
+    user: CurrentUser,
+    Path(grant_id): Path<String>,
+) -> ApiResult<StatusCode> {
+    let service = access_service(&state)?;
+    let grant = party_grant(service, &grant_id, user.id()).await?;
+    let revoked = service
+        .revoke(&grant, user.id())
+        .await
+        .map_err(|e| ApiError::internal(format!("Failed to revoke access: {e:#}")))?;
+    if !revoked {
+        return Err(ApiError::conflict("Grant is already closed"));
+    }
+    Ok(StatusCode::NO_CONTENT)
+}
@@ -0,0 +1,334 @@
+//! Workspace backup handlers.
+
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+
+use axum::{
+    Json,
+    extract::{Path, Query, State},
+    http::StatusCode,
+};
+use chrono::Utc;
+use tracing::{info, instrument, warn};
+
+use crate::auth::{CurrentUser, RequireAdmin};
+use crate::user::UserListQuery;
+use crate::user_env;
+use crate::workspace_backups::{
+    BackupResult, CreateBackupRequest, PruneReport, RestoreRequest, RestoreResult, Snapshot,
+    SnapshotDetail, SnapshotQuery, WorkspaceBackupService,
+};
+
+use crate::api::error::{ApiError, ApiResult};
+use crate::api::state::AppState;
+
+/// How often the scheduler looks for workspaces that are due.
+const SCHEDULE_CHECK_INTERVAL: Duration = Duration::from_secs(15 * 60);
+
+fn service(state: &AppState) -> ApiResult<&Arc<WorkspaceBackupService>> {
+    state
+        .workspace_backups
+        .as_ref()
+        .ok_or_else(|| ApiError::not_found("Workspace backups are disabled"))
+}
+
+fn check_workspace(name: &str) -> ApiResult<()> {
+    if user_env::valid_workspace_name(name) {
+        Ok(())
+    } else {
+        Err(ApiError::bad_request(format!(
+            "Invalid workspace name: {name}"
+        )))
+    }
+}
+
+async fn find_snapshot(
+    service: &WorkspaceBackupService,
+    user_id: &str,
+    workspace: &str,
+    snapshot_id: &str,
+) -> ApiResult<Snapshot> {
+    service
+        .get(user_id, workspace, snapshot_id)
+        .await
+        .map_err(|e| ApiError::internal(format!("Failed to read snapshot: {e:#}")))?
+        .ok_or_else(|| ApiError::not_found(format!("Snapshot {snapshot_id} not found")))
+}
+
+/// List the caller's snapshots, newest first.
+///
+/// GET /api/me/backups
+#[instrument(skip(state, user))]
+pub async fn list_workspace_backups(
+    State(state): State<AppState>,
+    user: CurrentUser,
+    Query(query): Query<SnapshotQuery>,
+) -> ApiResult<Json<Vec<Snapshot>>> {
+    let service = service(&state)?;
+    if let Some(workspace) = &query.workspace {
+        check_workspace(workspace)?;
+    }
+    let snapshots = service
+        .list(user.id(), query.workspace.as_deref())
+        .await
+        .map_err(|e| ApiError::internal(format!("Failed to list snapshots: {e:#}")))?;
+    Ok(Json(snapshots))
+}
+
+/// Snapshot one of the caller's workspaces now.
+///
+/// POST /api/me/backups
+#[instrument(skip(state, user))]
+pub async fn create_workspace_backup(
+    State(state): State<AppState>,
+    user: CurrentUser,
+    Json(request): Json<CreateBackupRequest>,
+) -> ApiResult<(StatusCode, Json<BackupResult>)> {
+    let service = service(&state)?;
+    check_workspace(&request.workspace)?;
+    let dir = state
+        .sessions
+        .for_user(user.id())
+        .workspace_root()
+        .join(&request.workspace);
+    if !dir.is_dir() {
+        return Err(ApiError::not_found(format!(
+            "Workspace {} not found",
+            request.workspace
+        )));
+    }
+    let result = service
+        .backup(user.id(), &request.workspace, &dir)
+        .await
+        .map_err(|e| ApiError::internal(format!("Backup failed: {e:#}")))?;
+    let status = if result.unchanged {
+        StatusCode::OK
+    } else {
+        StatusCode::CREATED
+    };
+    Ok((status, Json(result)))
+}
+
+/// A snapshot with its paths.
+///
+/// GET /api/me/backups/{workspace}/{snapshot_id}
+#[instrument(skip(state, user))]
+pub async fn get_workspace_backup(
+    State(state): State<AppState>,
+    user: CurrentUser,
+    Path((workspace, snapshot_id)): Path<(String, String)>,
+) -> ApiResult<Json<SnapshotDetail>> {
+    let service = service(&state)?;
+    check_workspace(&workspace)?;
+    let snapshot = find_snapshot(service, user.id(), &workspace, &snapshot_id).await?;
+    let detail = service
+        .detail(snapshot)
+        .await
+        .map_err(|e| ApiError::internal(format!("Failed to read snapshot: {e:#}")))?;
+    Ok(Json(detail))
+}
+
+/// Delete a snapshot. The storage it alone used is freed by the next prune.
+///
+/// DELETE /api/me/backups/{workspace}/{snapshot_id}
+#[instrument(skip(state, user))]
+pub async fn delete_workspace_backup(
+    State(state): State<AppState>,
+    user: CurrentUser,
+    Path((workspace, snapshot_id)): Path<(String, String)>,
+) -> ApiResult<StatusCode> {
+    let service = service(&state)?;
+    check_workspace(&workspace)?;
+    let snapshot = find_snapshot(service, user.id(), &workspace, &snapshot_id).await?;
+    service
+        .delete(&snapshot)
+        .await
+        .map_err(|e| ApiError::internal(format!("Failed to delete snapshot: {e:#}")))?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Restore a snapshot, or the workspace as it was at a point in time, into a
+/// new workspace or over the workspace itself.
+///
+/// POST /api/me/backups/{workspace}/restore
+#[instrument(skip(state, user, request))]
+pub async fn restore_workspace_backup(
+    State(state): State<AppState>,
+    user: CurrentUser,
+    Path(workspace): Path<String>,
+    Json(request): Json<RestoreRequest>,
+) -> ApiResult<Json<RestoreResult>> {
+    let service = service(&state)?;
+    check_workspace(&workspace)?;
+    let snapshot = service
+        .resolve(
+            user.id(),
+            &workspace,
+            request.snapshot_id.as_deref(),
+            request.at,
+        )
+        .await
+        .map_err(|e| ApiError::internal(format!("Failed to read snapshots: {e:#}")))?
+        .ok_or_else(|| ApiError::not_found(format!("No snapshot of {workspace} to restore")))?;
+
+    let linux_username = match state.linux_users.as_ref().filter(|lu| lu.enabled) {
+        Some(lu) => {
+            let account = state.users.get_user(user.id()).await?;
+            Some(
+                account
+                    .and_then(|account| account.linux_username)
+                    .unwrap_or_else(|| lu.linux_username(user.id())),
+            )
+        }
+        None => None,
+    };
+
+    let target = if request.in_place {
+        if linux_username.is_some() {
+            return Err(ApiError::bad_request(
+                "In-place restores are not available with per-user Linux accounts; \
+                 restore into a new workspace instead",
+            ));
+        }
+        workspace.clone()
+    } else {
+        let target = request.target.clone().unwrap_or_else(|| {
+            format!(
+                "{workspace}-restored-{}",
+                Utc::now().format("%Y%m%d-%H%M%S")
+            )
+        });
+        check_workspace(&target)?;
+        target
+    };
+    let dest = state
+        .sessions
+        .for_user(user.id())
+        .workspace_root()
+        .join(&target);
+    if !request.in_place && dest.exists() {
+        return Err(ApiError::conflict(format!(
+            "Workspace {target} already exists"
+        )));
+    }
+
+    let (files, bytes) = match linux_username {
+        None => service.restore(&snapshot, &dest, &request.paths).await,
+        Some(username) => restore_as(service, &snapshot, dest, &request.paths, username).await,
+    }
+    .map_err(|e| ApiError::internal(format!("Restore failed: {e:#}")))?;
+
+    info!(
+        user_id = %user.id(),
+        workspace = %workspace,
+        snapshot_id = %snapshot.id,
+        target = %target,
+        files,
+        bytes,
+        "Restored workspace backup"
+    );
+    Ok(Json(RestoreResult {
+        snapshot_id: snapshot.id,
+        workspace: target,
+        files,
+        bytes,
+    }))
+}
+
+/// Restore into a staging directory and have oqto-usermgr move the files
+/// into a new workspace owned by `username`.
+async fn restore_as(
+    service: &WorkspaceBackupService,
+    snapshot: &Snapshot,
+    dest: PathBuf,
+    paths: &[String],
+    username: String,
+) -> anyhow::Result<(u64, u64)> {
+    let staging = tempfile::tempdir()?;
+    let counts = service.restore(snapshot, staging.path(), paths).await?;
+    tokio::task::spawn_blocking(move || {
+        crate::local::linux_users::usermgr_request(
+            "create-workspace",
+            serde_json::json!({
+                "username": username,
+                "path": dest.to_string_lossy(),
+                "template_src": staging.path().to_string_lossy(),
+                "files": {},
+            }),
+        )
+    })
+    .await??;
+    Ok(counts)
+}
+
+/// Apply the retention policy and free unreferenced storage (admin only).
+///
+/// POST /api/admin/backups/prune
+#[instrument(skip(state, _admin))]
+pub async fn prune_workspace_backups(
+    State(state): State<AppState>,
+    RequireAdmin(_admin): RequireAdmin,
+) -> ApiResult<Json<PruneReport>> {
+    let report = service(&state)?
+        .prune()
+        .await
+        .map_err(|e| ApiError::internal(format!("Prune failed: {e:#}")))?;
+    Ok(Json(report))
+}
+
+/// Background loop that snapshots every active user's workspaces once
+/// their interval has passed, and prunes after a round that took any.
+pub async fn run_workspace_backups(state: AppState) {
+    let Some(service) = state.workspace_backups.clone() else {
+        return;
+    };
+    let mut interval = tokio::time::interval(SCHEDULE_CHECK_INTERVAL);
+    loop {
+        interval.tick().await;
+        let users = match state
+            .users
+            .list_users(UserListQuery {
+                is_active: Some(true),
+                ..Default::default()
+            })
+            .await
+        {
+            Ok(users) => users,
+            Err(e) => {
+                warn!("Failed to list users for workspace backups: {e:#}");
+                continue;
+            }
+        };
+
+        let mut taken = 0;
+        for user in users {
+            let root = state.sessions.for_user(&user.id).workspace_root();
+            let workspaces = match user_env::list_workspaces(&root) {
+                Ok(workspaces) => workspaces,
+                Err(e) => {
+                    warn!(user_id = %user.id, "Failed to list workspaces for backup: {e:#}");
+                    continue;
+                }
+            };
+            for workspace in workspaces {
+                match service
+                    .backup_if_due(&user.id, &workspace, &root.join(&workspace), Utc::now())
+                    .await
+                {
+                    Ok(Some(result)) if !result.unchanged => taken += 1,
+                    Ok(_) => {}
+                    Err(e) => {
+                        warn!(user_id = %user.id, workspace = %workspace, "Workspace backup failed: {e:#}")
+                    }
+                }
+            }
+        }
+
+        if taken > 0
+            && let Err(e) = service.prune().await
+        {
+            warn!("Failed to prune workspace backups: {e:#}");
+        }
+    }
+}
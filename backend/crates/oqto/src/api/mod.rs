@@ -10,6 +10,8 @@ pub mod handlers;
 mod onboarding_handlers;
 pub(crate) mod provisioning;
 pub mod proxy;
+mod rate_limit;
+mod rbac;
 mod routes;
 mod state;
 mod test_harness;
@@ -20,6 +22,7 @@ pub mod ws_multiplexed;
 // Re-export error types for external use
 #[allow(unused_imports)]
 pub use error::{ApiError, ApiResult, ErrorResponse};
+pub use rate_limit::{RateLimitConfig, RateLimiter};
 pub use routes::{create_admin_router_with_config, create_router_with_config};
 pub use state::{
     AppState, EavsConfigPaths, MmryState, SessionUiState, TemplatesRepoType, TemplatesState,
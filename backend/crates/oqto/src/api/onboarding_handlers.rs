@@ -223,6 +223,7 @@ pub async fn bootstrap_onboarding(
         language: language.clone(),
         pinned: Some(true),
         bootstrap_pending: Some(true),
+        thumbnail: None,
     };
 
     let meta_toml = toml::to_string_pretty(&meta)
@@ -336,6 +337,21 @@ pub async fn bootstrap_onboarding(
         );
     }
 
+    // Runs at most once per user; a no-op if registration already ran it.
+    if state.bootstrap_script.is_some() {
+        match state.users.get_user(user.id()).await {
+            Ok(Some(db_user)) => crate::api::provisioning::spawn_first_workspace_bootstrap(
+                &state,
+                &db_user,
+                workspace_path.clone(),
+            ),
+            Ok(None) => {}
+            Err(e) => {
+                tracing::warn!(user_id = %user.id(), error = ?e, "Failed to load user for bootstrap script (non-fatal)");
+            }
+        }
+    }
+
     let workspace_path_str = workspace_path.to_string_lossy().to_string();
 
     Ok(Json(BootstrapOnboardingResponse {
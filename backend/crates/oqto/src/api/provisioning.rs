@@ -3,11 +3,22 @@
 //! Keeps auth handlers focused on identity and delegates runtime bootstrap
 //! (mmry, Pi config, workspace scaffolding) to a dedicated module.
 
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use anyhow::{Context, Result, bail};
 use tracing::{info, warn};
 
 use crate::api::handlers::admin::provision_eavs_for_user;
 use crate::api::state::AppState;
+use crate::onboarding::{BootstrapScript, BootstrapScriptRun, BootstrapScriptStatus};
+use crate::runner::router::{ExecutionTarget, resolve_runner_for_target};
 use crate::user::User;
+use crate::ws::WsEvent;
+
+/// Poll interval while waiting for the bootstrap script to exit.
+const BOOTSTRAP_POLL_INTERVAL: Duration = Duration::from_millis(250);
 
 pub async fn bootstrap_new_user_environment(
     state: &AppState,
@@ -293,6 +304,7 @@ pub async fn bootstrap_new_user_environment(
         match crate::local::linux_users::usermgr_request("create-workspace", create_args) {
             Ok(_) => {
                 info!(user_id = %user.id, "Created default workspace for new user");
+                spawn_first_workspace_bootstrap(state, user, PathBuf::from(&workspace_path));
             }
             Err(e) => {
                 warn!(user_id = %user.id, error = ?e, "Failed to create default workspace (non-fatal)");
@@ -300,3 +312,149 @@ pub async fn bootstrap_new_user_environment(
         }
     }
 }
+
+/// Run the admin bootstrap script in a user's first workspace.
+///
+/// The script runs at most once per user (tracked in the onboarding state)
+/// in a background task, so a slow or failing script never blocks
+/// registration or login. The outcome and captured output are written to the
+/// onboarding record, and the user is notified if it failed.
+pub fn spawn_first_workspace_bootstrap(state: &AppState, user: &User, workspace_path: PathBuf) {
+    let (Some(script), Some(onboarding)) =
+        (state.bootstrap_script.clone(), state.onboarding.clone())
+    else {
+        return;
+    };
+    let state = state.clone();
+    let user = user.clone();
+
+    tokio::spawn(async move {
+        let workspace = workspace_path.to_string_lossy().into_owned();
+        let mut run = match onboarding
+            .begin_bootstrap_script(&user.id, &workspace)
+            .await
+        {
+            Ok(Some(run)) => run,
+            Ok(None) => return,
+            Err(e) => {
+                warn!(user_id = %user.id, error = ?e, "Failed to claim bootstrap script run (non-fatal)");
+                return;
+            }
+        };
+
+        info!(user_id = %user.id, workspace = %workspace, "Running bootstrap script");
+        match run_bootstrap_script(&state, &script, &user, &workspace_path, &mut run).await {
+            Ok(exit_code) => run.finish(exit_code, None),
+            Err(e) => run.finish(None, Some(format!("{e:#}"))),
+        }
+
+        if run.status == BootstrapScriptStatus::Succeeded {
+            info!(user_id = %user.id, "Bootstrap script succeeded");
+        } else {
+            warn!(
+                user_id = %user.id,
+                exit_code = ?run.exit_code,
+                error = ?run.error,
+                "Bootstrap script failed (non-fatal)"
+            );
+            state
+                .ws_hub
+                .send_to_user(
+                    &user.id,
+                    WsEvent::Notification {
+                        level: "warning".to_string(),
+                        title: "Workspace setup script failed".to_string(),
+                        message: "Your workspace is usable, but some organization tools may \
+                                  not be set up. Contact an administrator if this persists."
+                            .to_string(),
+                        category: "onboarding.bootstrap_script".to_string(),
+                        detail: Some(serde_json::json!({
+                            "workspace_path": run.workspace_path,
+                            "exit_code": run.exit_code,
+                            "error": run.error,
+                        })),
+                    },
+                )
+                .await;
+        }
+
+        if let Err(e) = onboarding.record_bootstrap_script(&user.id, run).await {
+            warn!(user_id = %user.id, error = ?e, "Failed to record bootstrap script result");
+        }
+    });
+}
+
+/// Spawn the script on the user's runner and collect its output.
+///
+/// Returns the exit code, or an error if the script could not be started or
+/// ran past its timeout.
+async fn run_bootstrap_script(
+    state: &AppState,
+    script: &BootstrapScript,
+    user: &User,
+    workspace_path: &Path,
+    run: &mut BootstrapScriptRun,
+) -> Result<Option<i32>> {
+    let runner = resolve_runner_for_target(state, &user.id, &ExecutionTarget::Personal)
+        .await?
+        .context("no runner available for user")?;
+
+    let process_id = format!("bootstrap-{}", user.id);
+    let env = HashMap::from([
+        ("OQTO_USER_ID".to_string(), user.id.clone()),
+        ("OQTO_USERNAME".to_string(), user.username.clone()),
+        ("OQTO_USER_EMAIL".to_string(), user.email.clone()),
+        (
+            "OQTO_USER_DISPLAY_NAME".to_string(),
+            user.display_name.clone(),
+        ),
+        (
+            "OQTO_WORKSPACE".to_string(),
+            workspace_path.to_string_lossy().into_owned(),
+        ),
+    ]);
+    runner
+        .spawn_rpc_process(
+            &process_id,
+            script.shell(),
+            script.args(),
+            workspace_path,
+            env,
+            script.sandboxed(),
+        )
+        .await
+        .context("starting bootstrap script")?;
+
+    let deadline = Instant::now() + script.timeout();
+    loop {
+        if Instant::now() >= deadline {
+            let _ = runner.kill_process(&process_id, true).await;
+            bail!("timed out after {}s", script.timeout().as_secs());
+        }
+        let chunk = runner
+            .read_stdout(&process_id, 1000)
+            .await
+            .context("reading bootstrap script output")?;
+        if script.append_output(&mut run.output, &chunk.data) {
+            run.output_truncated = true;
+        }
+        if !chunk.has_more {
+            break;
+        }
+    }
+
+    loop {
+        let status = runner
+            .get_status(&process_id)
+            .await
+            .context("checking bootstrap script status")?;
+        if !status.running {
+            return Ok(status.exit_code);
+        }
+        if Instant::now() >= deadline {
+            let _ = runner.kill_process(&process_id, true).await;
+            bail!("timed out after {}s", script.timeout().as_secs());
+        }
+        tokio::time::sleep(BOOTSTRAP_POLL_INTERVAL).await;
+    }
+}
@@ -3,13 +3,14 @@
 //! Provides common infrastructure for HTTP and WebSocket proxying:
 //! - Session lookup and validation
 //! - Retry logic with exponential backoff
-//! - Request forwarding with body limits
+//! - Streaming request forwarding with body limits
 //! - Error handling and status code mapping
 
 use axum::{
     body::Body,
     http::{HeaderMap, Request, Response, StatusCode, Uri},
 };
+use http_body_util::{LengthLimitError, Limited};
 use hyper_util::client::legacy::Client;
 use hyper_util::client::legacy::connect::HttpConnector;
 use log::{debug, error, warn};
@@ -68,21 +69,19 @@ pub async fn get_session_by_id(state: &AppState, session_id: &str) -> Result<Ses
 /// Get or create an IO session for a workspace path.
 pub async fn get_io_session_for_workspace(
     state: &AppState,
-    user: &CurrentUser,
+    user_id: &str,
     workspace_path: &str,
 ) -> Result<Session, StatusCode> {
-    let session_owner = match resolve_target_for_workspace_path(state, user.id(), workspace_path)
+    let session_owner = match resolve_target_for_workspace_path(state, user_id, workspace_path)
         .await
         .map_err(|e| {
             error!(
                 "Failed to resolve execution target for workspace {} and user {}: {:?}",
-                workspace_path,
-                user.id(),
-                e
+                workspace_path, user_id, e
             );
             StatusCode::SERVICE_UNAVAILABLE
         })? {
-        ExecutionTarget::Personal => user.id().to_string(),
+        ExecutionTarget::Personal => user_id.to_string(),
         ExecutionTarget::SharedWorkspace { workspace_id } => {
             let sw = state.shared_workspaces.as_ref().ok_or_else(|| {
                 error!(
@@ -190,8 +189,8 @@ pub fn enforce_proxy_body_limit(
 /// * `req` - The incoming request
 /// * `target_port` - The localhost port to proxy to
 /// * `target_path` - The path on the target server
-/// * `retry_on_connect` - Whether to retry connection errors (for starting services)
-/// * `max_body_bytes` - Maximum body size to accept
+/// * `retry_on_connect` - Whether to wait for the target to accept connections (for starting services)
+/// * `max_body_bytes` - Maximum request body size to stream through
 pub async fn proxy_http_request(
     client: Client<HttpConnector, Body>,
     req: Request<Body>,
@@ -248,104 +247,97 @@ pub async fn proxy_http_request_with_query(
     }
 
     enforce_proxy_body_limit(&parts.headers, max_body_bytes)?;
-    let body_bytes = axum::body::to_bytes(body, max_body_bytes)
-        .await
-        .map_err(|e| {
-            if e.to_string().contains("length limit") {
-                warn!(
-                    "Proxy request body exceeded limit of {} bytes",
-                    max_body_bytes
-                );
-                return StatusCode::PAYLOAD_TOO_LARGE;
-            }
-            error!("Failed to buffer proxy request body: {:?}", e);
-            StatusCode::BAD_GATEWAY
-        })?;
-
-    warn!("Proxy request body size: {} bytes", body_bytes.len());
 
-    let start = tokio::time::Instant::now();
-    let timeout = DEFAULT_STARTUP_TIMEOUT;
-    let mut attempts: u32 = 0;
+    // The body is streamed through, so the request can only be sent once.
+    // For services that are still starting, wait until the port accepts
+    // connections instead of retrying the request.
+    if retry_on_connect {
+        wait_for_target(target_port, DEFAULT_STARTUP_TIMEOUT).await?;
+    }
 
-    let response = loop {
-        attempts += 1;
-        let mut forwarded = Request::builder()
-            .method(parts.method.clone())
-            .uri(uri.clone())
-            .version(parts.version)
-            .body(Body::from(body_bytes.clone()))
-            .map_err(|e| {
-                error!("Failed to build proxy request: {:?}", e);
-                StatusCode::INTERNAL_SERVER_ERROR
-            })?;
+    let mut forwarded = Request::builder()
+        .method(parts.method)
+        .uri(uri)
+        .version(parts.version)
+        .body(Body::new(Limited::new(body, max_body_bytes)))
+        .map_err(|e| {
+            error!("Failed to build proxy request: {:?}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
 
-        *forwarded.headers_mut() = parts.headers.clone();
+    // Content-Length and Transfer-Encoding are forwarded as received; the
+    // body reaches the target unchanged.
+    *forwarded.headers_mut() = parts.headers;
 
-        // Update Content-Length to match actual body size (important for multipart uploads
-        // where the original Content-Length may not match after buffering).
-        // Also remove Transfer-Encoding since we're sending a fixed-length body.
-        forwarded
-            .headers_mut()
-            .remove(axum::http::header::TRANSFER_ENCODING);
-        let new_content_length = body_bytes.len().to_string();
-        warn!(
-            "Setting Content-Length to {} (was: {:?})",
-            new_content_length,
-            parts.headers.get(axum::http::header::CONTENT_LENGTH)
-        );
-        let content_length =
-            axum::http::HeaderValue::from_str(&new_content_length).map_err(|e| {
-                error!(
-                    "Invalid content-length header value {}: {:?}",
-                    new_content_length, e
-                );
-                StatusCode::INTERNAL_SERVER_ERROR
-            })?;
+    // Ensure Host header matches the target authority.
+    if let Some(authority) = forwarded.uri().authority() {
+        let value = axum::http::HeaderValue::from_str(authority.as_str()).map_err(|e| {
+            error!("Invalid Host header value {}: {:?}", authority.as_str(), e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
         forwarded
             .headers_mut()
-            .insert(axum::http::header::CONTENT_LENGTH, content_length);
+            .insert(axum::http::header::HOST, value);
+    }
 
-        // Ensure Host header matches the target authority.
-        if let Some(authority) = forwarded.uri().authority() {
-            let value = axum::http::HeaderValue::from_str(authority.as_str()).map_err(|e| {
-                error!("Invalid Host header value {}: {:?}", authority.as_str(), e);
-                StatusCode::INTERNAL_SERVER_ERROR
-            })?;
-            forwarded
-                .headers_mut()
-                .insert(axum::http::header::HOST, value);
+    let response = client.request(forwarded).await.map_err(|err| {
+        if exceeded_body_limit(&err) {
+            warn!(
+                "Proxy request body exceeded limit of {} bytes",
+                max_body_bytes
+            );
+            return StatusCode::PAYLOAD_TOO_LARGE;
         }
-
-        match client.request(forwarded).await {
-            Ok(res) => break res,
-            Err(err) => {
-                if retry_on_connect && err.is_connect() && start.elapsed() < timeout {
-                    let backoff_ms = (attempts.min(20) as u64) * 100;
-                    let backoff = Duration::from_millis(backoff_ms);
-                    debug!(
-                        "Proxy target not ready yet (attempt {}): {}; retrying in {:?}",
-                        attempts, err, backoff
-                    );
-                    tokio::time::sleep(backoff).await;
-                    continue;
-                }
-
-                error!("Proxy request failed: {:?}", err);
-                return Err(if retry_on_connect && err.is_connect() {
-                    StatusCode::SERVICE_UNAVAILABLE
-                } else {
-                    StatusCode::BAD_GATEWAY
-                });
-            }
+        error!("Proxy request failed: {:?}", err);
+        if retry_on_connect && err.is_connect() {
+            StatusCode::SERVICE_UNAVAILABLE
+        } else {
+            StatusCode::BAD_GATEWAY
         }
-    };
+    })?;
 
     // Convert hyper response to axum response
     let (parts, body) = response.into_parts();
     Ok(Response::from_parts(parts, Body::new(body)))
 }
 
+/// Wait until the target port accepts connections, backing off between
+/// attempts.
+async fn wait_for_target(port: u16, timeout: Duration) -> Result<(), StatusCode> {
+    let start = tokio::time::Instant::now();
+    let mut attempts: u32 = 0;
+    loop {
+        attempts += 1;
+        match tokio::net::TcpStream::connect(("localhost", port)).await {
+            Ok(_) => return Ok(()),
+            Err(err) if start.elapsed() < timeout => {
+                let backoff = Duration::from_millis((attempts.min(20) as u64) * 100);
+                debug!(
+                    "Proxy target not ready yet (attempt {}): {}; retrying in {:?}",
+                    attempts, err, backoff
+                );
+                tokio::time::sleep(backoff).await;
+            }
+            Err(err) => {
+                error!("Proxy target port {} not ready: {}", port, err);
+                return Err(StatusCode::SERVICE_UNAVAILABLE);
+            }
+        }
+    }
+}
+
+/// Whether a proxy request failed because its body outgrew the limit.
+fn exceeded_body_limit(err: &(dyn std::error::Error + 'static)) -> bool {
+    let mut source = Some(err);
+    while let Some(err) = source {
+        if err.is::<LengthLimitError>() {
+            return true;
+        }
+        source = err.source();
+    }
+    false
+}
+
 // ============================================================================
 // Query String Utilities
 // ============================================================================
@@ -425,6 +417,14 @@ pub mod tests {
         );
     }
 
+    #[tokio::test]
+    async fn exceeded_body_limit_detects_wrapped_error() {
+        let body = Body::new(Limited::new(Body::from("too long"), 3));
+        let err = axum::body::to_bytes(body, usize::MAX).await.unwrap_err();
+        assert!(exceeded_body_limit(&err));
+        assert!(!exceeded_body_limit(&std::io::Error::other("reset")));
+    }
+
     #[test]
     fn build_fileserver_query_adds_directory() {
         let result = build_fileserver_query("/home/user/project", None);
@@ -0,0 +1,858 @@
+//! Reverse proxy for dev servers started inside a user's sessions.
+//!
+//! Lets the frontend iframe a hot-reloading dev server (Vite, Next.js, ...)
+//! through the authenticated API origin instead of talking to the port
+//! directly. HTTP requests are streamed through, WebSocket upgrades (HMR) are
+//! relayed, and headers are rewritten so the upstream sees a local request
+//! while the browser sees a frameable response.
+//!
+//! Dev server pages are written by agents, so every proxied response carries
+//! a `sandbox` content security policy without `allow-same-origin`: the page
+//! runs in an opaque origin and its scripts cannot read the user's session or
+//! call the API as them, although it is served from the Oqto origin.
+//!
+//! Session previews (`/api/sessions/{id}/preview/{port}`) resolve the port
+//! through the session instead: loopback listeners of the owner in local mode,
+//! listeners on all interfaces of the container in container mode. Changes of
+//! a running session's ports are announced as `preview.ports`.
+
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::time::Duration;
+
+use axum::{
+    Json,
+    body::Body,
+    extract::{FromRequestParts, OriginalUri, Path, State, WebSocketUpgrade},
+    http::{HeaderMap, HeaderName, HeaderValue, Request, StatusCode, Uri, header},
+    response::{IntoResponse, Response},
+};
+use log::{debug, error, warn};
+use serde::{Deserialize, Serialize};
+use tokio_tungstenite::tungstenite::client::IntoClientRequest;
+
+use crate::auth::CurrentUser;
+use crate::session::{RuntimeMode, Session, SessionStatus};
+use crate::ws::WsEvent;
+
+use super::super::state::AppState;
+use super::builder::get_session_for_user;
+use super::ports::{ListeningPort, detect_listening_ports, parse_exposed_ports};
+use super::websocket::handle_dev_server_ws_proxy;
+
+/// Headers that only apply to a single HTTP hop.
+const HOP_BY_HOP_HEADERS: &[&str] = &[
+    "connection",
+    "keep-alive",
+    "proxy-authenticate",
+    "proxy-authorization",
+    "te",
+    "trailer",
+    "transfer-encoding",
+    "upgrade",
+];
+
+/// Session cookie carrying the Oqto auth token; never forwarded upstream.
+const AUTH_COOKIE: &str = "auth_token";
+
+/// Policy added to every proxied response. Leaving out `allow-same-origin`
+/// gives the page an opaque origin, isolating it from the Oqto UI and API.
+const PREVIEW_SANDBOX_POLICY: &str = "sandbox allow-scripts allow-forms allow-popups";
+
+/// Reads the socket tables inside a container session.
+const CONTAINER_SOCKET_TABLES: &[&str] = &[
+    "sh",
+    "-c",
+    "cat /proc/net/tcp /proc/net/tcp6 2>/dev/null; true",
+];
+
+/// Oqto's own services inside session containers (agent, fileserver, ttyd,
+/// mmry, browser stream); never offered as previews.
+const CONTAINER_SERVICE_PORTS: std::ops::RangeInclusive<u16> = 41820..=41824;
+
+/// Dev server proxy configuration.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DevProxyConfig {
+    /// Enable the dev server proxy.
+    ///
+    /// Proxied apps are served from the Oqto origin, sandboxed into an opaque
+    /// origin so their scripts cannot act as the user.
+    pub enabled: bool,
+    /// Lowest port that may be proxied.
+    pub min_port: u16,
+    /// Highest port that may be proxied.
+    pub max_port: u16,
+    /// Ports that are never proxied (e.g. the Oqto server itself).
+    pub blocked_ports: Vec<u16>,
+    /// Only proxy ports whose listening socket is owned by the requesting
+    /// user's Linux account.
+    pub require_owner: bool,
+    /// How often running sessions are scanned for new listening ports.
+    pub port_scan_interval_secs: u64,
+}
+
+impl Default for DevProxyConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            min_port: 1024,
+            max_port: 65535,
+            blocked_ports: Vec::new(),
+            require_owner: true,
+            port_scan_interval_secs: 5,
+        }
+    }
+}
+
+impl DevProxyConfig {
+    fn allows_port(&self, port: u16) -> bool {
+        (self.min_port..=self.max_port).contains(&port) && !self.blocked_ports.contains(&port)
+    }
+}
+
+// ============================================================================
+// Handlers
+// ============================================================================
+
+/// List dev server ports that can be proxied for the current user.
+///
+/// GET /api/dev-proxy/ports
+pub async fn list_dev_server_ports(
+    State(state): State<AppState>,
+    user: CurrentUser,
+) -> Result<Json<Vec<ListeningPort>>, StatusCode> {
+    let config = &state.dev_proxy;
+    if !config.enabled {
+        return Err(StatusCode::SERVICE_UNAVAILABLE);
+    }
+
+    let mut ports = detect_listening_ports(owner_uid(&state, user.id())?);
+    ports.retain(|p| config.allows_port(p.port));
+    Ok(Json(ports))
+}
+
+/// Proxy a request to the root of a dev server.
+///
+/// ANY /api/dev-proxy/{port}
+pub async fn proxy_dev_server_root(
+    State(state): State<AppState>,
+    user: CurrentUser,
+    Path(port): Path<u16>,
+    req: Request<Body>,
+) -> Result<Response, StatusCode> {
+    proxy_dev_server_inner(state, user, port, req).await
+}
+
+/// Proxy a request (HTTP or WebSocket upgrade) to a dev server.
+///
+/// ANY /api/dev-proxy/{port}/{*path}
+pub async fn proxy_dev_server(
+    State(state): State<AppState>,
+    user: CurrentUser,
+    Path((port, _path)): Path<(u16, String)>,
+    req: Request<Body>,
+) -> Result<Response, StatusCode> {
+    proxy_dev_server_inner(state, user, port, req).await
+}
+
+async fn proxy_dev_server_inner(
+    state: AppState,
+    user: CurrentUser,
+    port: u16,
+    req: Request<Body>,
+) -> Result<Response, StatusCode> {
+    if !state.dev_proxy.enabled {
+        return Err(StatusCode::SERVICE_UNAVAILABLE);
+    }
+    if !state.dev_proxy.allows_port(port) {
+        warn!("Dev proxy: port {} rejected by configuration", port);
+        return Err(StatusCode::FORBIDDEN);
+    }
+
+    let target = detect_listening_ports(owner_uid(&state, user.id())?)
+        .into_iter()
+        .find(|p| p.port == port)
+        .ok_or_else(|| {
+            debug!(
+                "Dev proxy: no listener on port {} for user {}",
+                port,
+                user.id()
+            );
+            StatusCode::NOT_FOUND
+        })?;
+
+    let marker = format!("/dev-proxy/{port}");
+    forward(state, &user, &marker, &target.authority(), port, req).await
+}
+
+/// List the ports of a running session that can be previewed.
+///
+/// GET /api/sessions/{session_id}/preview
+pub async fn list_session_preview_ports(
+    State(state): State<AppState>,
+    user: CurrentUser,
+    Path(session_id): Path<String>,
+) -> Result<Json<Vec<u16>>, StatusCode> {
+    if !state.dev_proxy.enabled {
+        return Err(StatusCode::SERVICE_UNAVAILABLE);
+    }
+
+    let session = get_session_for_user(&state, &user, &session_id).await?;
+    let targets = session_preview_targets(&state, &session).await?;
+    Ok(Json(targets.into_iter().map(|(port, _)| port).collect()))
+}
+
+/// Proxy a request to the root of a dev server inside a session.
+///
+/// ANY /api/sessions/{session_id}/preview/{port}
+pub async fn proxy_session_preview_root(
+    State(state): State<AppState>,
+    user: CurrentUser,
+    Path((session_id, port)): Path<(String, u16)>,
+    req: Request<Body>,
+) -> Result<Response, StatusCode> {
+    proxy_session_preview_inner(state, user, &session_id, port, req).await
+}
+
+/// Proxy a request (HTTP or WebSocket upgrade) to a dev server inside a session.
+///
+/// ANY /api/sessions/{session_id}/preview/{port}/{*path}
+pub async fn proxy_session_preview(
+    State(state): State<AppState>,
+    user: CurrentUser,
+    Path((session_id, port, _path)): Path<(String, u16, String)>,
+    req: Request<Body>,
+) -> Result<Response, StatusCode> {
+    proxy_session_preview_inner(state, user, &session_id, port, req).await
+}
+
+async fn proxy_session_preview_inner(
+    state: AppState,
+    user: CurrentUser,
+    session_id: &str,
+    port: u16,
+    req: Request<Body>,
+) -> Result<Response, StatusCode> {
+    if !state.dev_proxy.enabled {
+        return Err(StatusCode::SERVICE_UNAVAILABLE);
+    }
+    if !state.dev_proxy.allows_port(port) {
+        warn!("Session preview: port {} rejected by configuration", port);
+        return Err(StatusCode::FORBIDDEN);
+    }
+
+    let session = get_session_for_user(&state, &user, session_id).await?;
+    let authority = session_preview_targets(&state, &session)
+        .await?
+        .into_iter()
+        .find_map(|(p, authority)| (p == port).then_some(authority))
+        .ok_or_else(|| {
+            debug!(
+                "Session preview: no listener on port {} in session {}",
+                port, session_id
+            );
+            StatusCode::NOT_FOUND
+        })?;
+
+    let marker = format!("/preview/{port}");
+    forward(state, &user, &marker, &authority, port, req).await
+}
+
+/// Stream a request to the dev server at `authority`. `marker` is the path
+/// segment after which the upstream path starts.
+async fn forward(
+    state: AppState,
+    user: &CurrentUser,
+    marker: &str,
+    authority: &str,
+    port: u16,
+    req: Request<Body>,
+) -> Result<Response, StatusCode> {
+    let (mut parts, body) = req.into_parts();
+    let (prefix, upstream_path) = split_proxy_path(&parts.uri, parts.extensions.get(), marker);
+
+    if is_websocket_upgrade(&parts.headers) {
+        let ws = WebSocketUpgrade::from_request_parts(&mut parts, &state)
+            .await
+            .map_err(|_| StatusCode::BAD_REQUEST)?;
+        return proxy_dev_server_ws(ws, &parts.headers, authority, &upstream_path).await;
+    }
+
+    let uri: Uri = format!("http://{authority}{upstream_path}")
+        .parse()
+        .map_err(|e| {
+            error!("Dev proxy: invalid upstream URI for {}: {:?}", authority, e);
+            StatusCode::BAD_REQUEST
+        })?;
+
+    let meter = state.proxy_transfers.meter(user.id());
+    let mut forwarded = Request::builder()
+        .method(parts.method.clone())
+        .uri(uri)
+        .body(body)
+        .map_err(|e| {
+            error!("Dev proxy: failed to build request: {:?}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+    *forwarded.headers_mut() = parts.headers;
+    rewrite_request_headers(forwarded.headers_mut(), authority, &prefix)?;
+    let forwarded = meter.count_request(forwarded);
+
+    let response = state.http_client.request(forwarded).await.map_err(|e| {
+        warn!("Dev proxy request to {} failed: {:?}", authority, e);
+        StatusCode::BAD_GATEWAY
+    })?;
+
+    let (mut parts, body) = response.into_parts();
+    rewrite_response_headers(&mut parts.headers, &prefix, port);
+    Ok(meter.count_response(Response::from_parts(parts, Body::new(body))))
+}
+
+async fn proxy_dev_server_ws(
+    ws: WebSocketUpgrade,
+    headers: &HeaderMap,
+    authority: &str,
+    upstream_path: &str,
+) -> Result<Response, StatusCode> {
+    let mut upstream = format!("ws://{authority}{upstream_path}")
+        .into_client_request()
+        .map_err(|e| {
+            error!("Dev proxy: invalid upstream WebSocket URL: {:?}", e);
+            StatusCode::BAD_REQUEST
+        })?;
+
+    // HMR clients select their channel via the subprotocol (e.g. `vite-hmr`).
+    for name in [header::SEC_WEBSOCKET_PROTOCOL, header::USER_AGENT] {
+        if let Some(value) = headers.get(&name) {
+            upstream.headers_mut().insert(name, value.clone());
+        }
+    }
+    if let Some(cookie) = headers.get(header::COOKIE).and_then(strip_auth_cookie) {
+        upstream.headers_mut().insert(header::COOKIE, cookie);
+    }
+    if headers.contains_key(header::ORIGIN) {
+        let origin = HeaderValue::from_str(&format!("http://{authority}"))
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        upstream.headers_mut().insert(header::ORIGIN, origin);
+    }
+
+    // Connect before upgrading so failures surface as HTTP errors and the
+    // negotiated subprotocol can be echoed back to the browser.
+    let (server_socket, handshake) =
+        tokio_tungstenite::connect_async(upstream)
+            .await
+            .map_err(|e| {
+                warn!(
+                    "Dev proxy: WebSocket connect to {} failed: {}",
+                    authority, e
+                );
+                StatusCode::BAD_GATEWAY
+            })?;
+
+    let protocol = handshake
+        .headers()
+        .get(header::SEC_WEBSOCKET_PROTOCOL)
+        .and_then(|v| v.to_str().ok())
+        .map(str::to_string);
+
+    let ws = match protocol {
+        Some(protocol) => ws.protocols([protocol]),
+        None => ws,
+    };
+
+    let authority = authority.to_string();
+    Ok(ws
+        .on_upgrade(move |socket| async move {
+            if let Err(e) = handle_dev_server_ws_proxy(socket, server_socket).await {
+                debug!("Dev proxy WebSocket to {} closed: {:?}", authority, e);
+            }
+        })
+        .into_response())
+}
+
+// ============================================================================
+// Helpers
+// ============================================================================
+
+/// Uid whose listeners the user may reach, or `None` when ownership is not enforced.
+fn owner_uid(state: &AppState, user_id: &str) -> Result<Option<u32>, StatusCode> {
+    if !state.dev_proxy.require_owner {
+        return Ok(None);
+    }
+    let linux_user = state.effective_linux_username(user_id);
+    crate::runner::router::resolve_linux_uid(&linux_user)
+        .map(Some)
+        .map_err(|e| {
+            error!(
+                "Dev proxy: failed to resolve uid for linux user {}: {:?}",
+                linux_user, e
+            );
+            StatusCode::SERVICE_UNAVAILABLE
+        })
+}
+
+/// Ports of a running session that can be previewed, with the `host:port`
+/// to dial for each.
+async fn session_preview_targets(
+    state: &AppState,
+    session: &Session,
+) -> Result<Vec<(u16, String)>, StatusCode> {
+    if session.status != SessionStatus::Running {
+        return Err(StatusCode::CONFLICT);
+    }
+
+    let mut targets: Vec<(u16, String)> = match session.runtime_mode {
+        RuntimeMode::Local => detect_listening_ports(owner_uid(state, &session.user_id)?)
+            .into_iter()
+            .map(|p| (p.port, p.authority()))
+            .collect(),
+        RuntimeMode::Container => {
+            let tables = state
+                .sessions
+                .exec_in_session(session, CONTAINER_SOCKET_TABLES)
+                .await
+                .map_err(|e| {
+                    warn!(
+                        "Session preview: failed to read listeners of session {}: {:?}",
+                        session.id, e
+                    );
+                    StatusCode::SERVICE_UNAVAILABLE
+                })?;
+            let ip = state
+                .sessions
+                .session_container_ip(session)
+                .await
+                .map_err(|e| {
+                    warn!(
+                        "Session preview: failed to resolve address of session {}: {:?}",
+                        session.id, e
+                    );
+                    StatusCode::SERVICE_UNAVAILABLE
+                })?
+                .ok_or_else(|| {
+                    warn!(
+                        "Session preview: session {} has no container address",
+                        session.id
+                    );
+                    StatusCode::SERVICE_UNAVAILABLE
+                })?;
+            parse_exposed_ports(&tables)
+                .into_iter()
+                .filter(|port| !CONTAINER_SERVICE_PORTS.contains(port))
+                .map(|port| (port, SocketAddr::new(ip, port).to_string()))
+                .collect()
+        }
+    };
+    targets.retain(|(port, _)| state.dev_proxy.allows_port(*port));
+    Ok(targets)
+}
+
+/// Scan running sessions for listening ports and announce changes to their
+/// owners as `preview.ports`. Runs until shutdown.
+pub async fn run_preview_port_watch(state: AppState) {
+    if !state.dev_proxy.enabled {
+        return;
+    }
+    let mut interval = tokio::time::interval(Duration::from_secs(
+        state.dev_proxy.port_scan_interval_secs.max(1),
+    ));
+    interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+    // Last announced ports per session, with the session owner.
+    let mut known: HashMap<String, (String, Vec<u16>)> = HashMap::new();
+    loop {
+        interval.tick().await;
+        let sessions = match state.sessions.list_active_sessions().await {
+            Ok(sessions) => sessions,
+            Err(e) => {
+                warn!("Session preview: failed to list sessions: {:?}", e);
+                continue;
+            }
+        };
+
+        let mut current = HashMap::new();
+        for session in sessions {
+            if session.status != SessionStatus::Running {
+                continue;
+            }
+            // Keep the last known ports when a scan fails.
+            let ports = match session_preview_targets(&state, &session).await {
+                Ok(targets) => targets.into_iter().map(|(port, _)| port).collect(),
+                Err(_) => match known.get(&session.id) {
+                    Some((_, ports)) => ports.clone(),
+                    None => continue,
+                },
+            };
+            current.insert(session.id, (session.user_id, ports));
+        }
+
+        for (session_id, user_id, ports) in port_changes(&known, &current) {
+            state
+                .ws_hub
+                .send_to_user(&user_id, WsEvent::PreviewPorts { session_id, ports })
+                .await;
+        }
+        known = current;
+    }
+}
+
+/// `(session, owner, ports)` for every session whose ports differ between two
+/// scans. Sessions that stopped report no ports.
+fn port_changes(
+    previous: &HashMap<String, (String, Vec<u16>)>,
+    current: &HashMap<String, (String, Vec<u16>)>,
+) -> Vec<(String, String, Vec<u16>)> {
+    let mut changes: Vec<(String, String, Vec<u16>)> = current
+        .iter()
+        .filter(|(id, (_, ports))| {
+            previous
+                .get(*id)
+                .map_or(!ports.is_empty(), |(_, before)| before != ports)
+        })
+        .map(|(id, (user_id, ports))| (id.clone(), user_id.clone(), ports.clone()))
+        .collect();
+    changes.extend(
+        previous
+            .iter()
+            .filter(|(id, (_, ports))| !current.contains_key(*id) && !ports.is_empty())
+            .map(|(id, (user_id, _))| (id.clone(), user_id.clone(), Vec::new())),
+    );
+    changes.sort();
+    changes
+}
+
+fn is_websocket_upgrade(headers: &HeaderMap) -> bool {
+    headers
+        .get(header::UPGRADE)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| v.eq_ignore_ascii_case("websocket"))
+}
+
+/// Split the request path into the public proxy prefix (as seen by the
+/// browser, e.g. `/api/dev-proxy/5173`) and the upstream path with query.
+/// `marker` is the last part of the prefix (e.g. `/dev-proxy/5173`).
+fn split_proxy_path(uri: &Uri, original: Option<&OriginalUri>, marker: &str) -> (String, String) {
+    let path = uri.path();
+    let rest = path
+        .find(marker)
+        .map(|idx| &path[idx + marker.len()..])
+        .unwrap_or("");
+    let rest = if rest.is_empty() { "/" } else { rest };
+
+    let public_path = original.map(|o| o.0.path()).unwrap_or(path);
+    let prefix = public_path
+        .find(marker)
+        .map(|idx| public_path[..idx + marker.len()].to_string())
+        .unwrap_or_else(|| marker.to_string());
+
+    let upstream = match uri.query() {
+        Some(query) => format!("{rest}?{query}"),
+        None => rest.to_string(),
+    };
+    (prefix, upstream)
+}
+
+/// Make the request look local to the dev server and drop Oqto credentials.
+fn rewrite_request_headers(
+    headers: &mut HeaderMap,
+    authority: &str,
+    prefix: &str,
+) -> Result<(), StatusCode> {
+    let original_host = headers.get(header::HOST).cloned();
+    for name in HOP_BY_HOP_HEADERS {
+        headers.remove(*name);
+    }
+    headers.remove(header::AUTHORIZATION);
+    if let Some(cookie) = headers.remove(header::COOKIE)
+        && let Some(cookie) = strip_auth_cookie(&cookie)
+    {
+        headers.insert(header::COOKIE, cookie);
+    }
+
+    let local = |value: String| {
+        HeaderValue::from_str(&value).map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)
+    };
+    // Dev servers commonly validate Host/Origin against localhost.
+    headers.insert(header::HOST, local(authority.to_string())?);
+    if headers.contains_key(header::ORIGIN) {
+        headers.insert(header::ORIGIN, local(format!("http://{authority}"))?);
+    }
+    if let Some(host) = original_host {
+        headers.insert(HeaderName::from_static("x-forwarded-host"), host);
+    }
+    headers
+        .entry(HeaderName::from_static("x-forwarded-proto"))
+        .or_insert(HeaderValue::from_static("http"));
+    headers.insert(
+        HeaderName::from_static("x-forwarded-prefix"),
+        local(prefix.to_string())?,
+    );
+    Ok(())
+}
+
+/// Allow framing, sandbox the page and keep redirects and cookies under the
+/// proxy prefix.
+fn rewrite_response_headers(headers: &mut HeaderMap, prefix: &str, port: u16) {
+    headers.remove(header::X_FRAME_OPTIONS);
+
+    if let Some(csp) = headers.remove(header::CONTENT_SECURITY_POLICY)
+        && let Ok(csp) = csp.to_str()
+    {
+        let kept: Vec<&str> = csp
+            .split(';')
+            .map(str::trim)
+            .filter(|d| !d.is_empty() && !d.starts_with("frame-ancestors"))
+            .collect();
+        if !kept.is_empty()
+            && let Ok(value) = HeaderValue::from_str(&kept.join("; "))
+        {
+            headers.insert(header::CONTENT_SECURITY_POLICY, value);
+        }
+    }
+    // A separate policy, so a `sandbox` directive of the upstream can only
+    // restrict the page further.
+    headers.append(
+        header::CONTENT_SECURITY_POLICY,
+        HeaderValue::from_static(PREVIEW_SANDBOX_POLICY),
+    );
+
+    if let Some(location) = headers.get(header::LOCATION).and_then(|v| v.to_str().ok())
+        && let Some(rewritten) = rewrite_location(location, prefix, port)
+        && let Ok(value) = HeaderValue::from_str(&rewritten)
+    {
+        headers.insert(header::LOCATION, value);
+    }
+
+    let cookies: Vec<HeaderValue> = headers
+        .get_all(header::SET_COOKIE)
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .filter_map(|v| HeaderValue::from_str(&rewrite_set_cookie(v, prefix)).ok())
+        .collect();
+    if !cookies.is_empty() {
+        headers.remove(header::SET_COOKIE);
+        for cookie in cookies {
+            headers.append(header::SET_COOKIE, cookie);
+        }
+    }
+}
+
+/// Rewrite a redirect target that points at the dev server itself.
+fn rewrite_location(location: &str, prefix: &str, port: u16) -> Option<String> {
+    let path = ["localhost", "127.0.0.1", "[::1]"]
+        .iter()
+        .find_map(|host| {
+            let origin = format!("http://{host}:{port}");
+            location
+                .strip_prefix(origin.as_str())
+                .filter(|rest| rest.is_empty() || rest.starts_with(['/', '?']))
+        })
+        .unwrap_or(location);
+
+    if path.is_empty() {
+        return Some(format!("{prefix}/"));
+    }
+    if path.starts_with('/') && !path.starts_with("//") && !path.starts_with(prefix) {
+        return Some(format!("{prefix}{path}"));
+    }
+    None
+}
+
+/// Scope cookies to the proxy prefix and drop any domain restriction.
+fn rewrite_set_cookie(cookie: &str, prefix: &str) -> String {
+    cookie
+        .split(';')
+        .map(str::trim)
+        .filter(|attr| !attr.to_ascii_lowercase().starts_with("domain="))
+        .map(|attr| match attr.split_once('=') {
+            Some((name, path)) if name.eq_ignore_ascii_case("path") && path.starts_with('/') => {
+                format!("Path={prefix}{}", path.trim_end_matches('/'))
+            }
+            _ => attr.to_string(),
+        })
+        .collect::<Vec<_>>()
+        .join("; ")
+}
+
+/// Remove the Oqto auth cookie from a `Cookie` header, keeping the rest.
+fn strip_auth_cookie(cookie: &HeaderValue) -> Option<HeaderValue> {
+    let kept: Vec<&str> = cookie
+        .to_str()
+        .ok()?
+        .split(';')
+        .map(str::trim)
+        .filter(|pair| {
+            !pair.is_empty()
+                && pair
+                    .split_once('=')
+                    .is_none_or(|(name, _)| name.trim() != AUTH_COOKIE)
+        })
+        .collect();
+    if kept.is_empty() {
+        return None;
+    }
+    HeaderValue::from_str(&kept.join("; ")).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn split_proxy_path_uses_original_prefix() {
+        let uri: Uri = "/dev-proxy/5173/src/main.ts?t=1".parse().unwrap();
+        let original = OriginalUri("/api/dev-proxy/5173/src/main.ts?t=1".parse().unwrap());
+        let (prefix, upstream) = split_proxy_path(&uri, Some(&original), "/dev-proxy/5173");
+        assert_eq!(prefix, "/api/dev-proxy/5173");
+        assert_eq!(upstream, "/src/main.ts?t=1");
+
+        let uri: Uri = "/dev-proxy/5173".parse().unwrap();
+        let (prefix, upstream) = split_proxy_path(&uri, None, "/dev-proxy/5173");
+        assert_eq!(prefix, "/dev-proxy/5173");
+        assert_eq!(upstream, "/");
+    }
+
+    #[test]
+    fn split_proxy_path_for_session_preview() {
+        let uri: Uri = "/sessions/s1/preview/3000/@vite/client".parse().unwrap();
+        let original = OriginalUri(
+            "/api/sessions/s1/preview/3000/@vite/client"
+                .parse()
+                .unwrap(),
+        );
+        let (prefix, upstream) = split_proxy_path(&uri, Some(&original), "/preview/3000");
+        assert_eq!(prefix, "/api/sessions/s1/preview/3000");
+        assert_eq!(upstream, "/@vite/client");
+    }
+
+    #[test]
+    fn port_changes_announce_new_and_stopped_sessions() {
+        let entry = |user: &str, ports: &[u16]| (user.to_string(), ports.to_vec());
+        let previous = HashMap::from([
+            ("a".to_string(), entry("u1", &[3000])),
+            ("b".to_string(), entry("u1", &[5173])),
+            ("c".to_string(), entry("u2", &[8080])),
+        ]);
+        let current = HashMap::from([
+            ("a".to_string(), entry("u1", &[3000])),
+            ("b".to_string(), entry("u1", &[5173, 8000])),
+            ("d".to_string(), entry("u2", &[])),
+        ]);
+        assert_eq!(
+            port_changes(&previous, &current),
+            vec![
+                ("b".to_string(), "u1".to_string(), vec![5173, 8000]),
+                ("c".to_string(), "u2".to_string(), vec![]),
+            ]
+        );
+    }
+
+    #[test]
+    fn request_headers_drop_credentials() {
+        let mut headers = HeaderMap::new();
+        headers.insert(header::HOST, HeaderValue::from_static("oqto.example.com"));
+        headers.insert(header::AUTHORIZATION, HeaderValue::from_static("Bearer x"));
+        headers.insert(
+            header::COOKIE,
+            HeaderValue::from_static("auth_token=secret; theme=dark"),
+        );
+        headers.insert(header::ORIGIN, HeaderValue::from_static("https://oqto"));
+
+        rewrite_request_headers(&mut headers, "127.0.0.1:5173", "/api/dev-proxy/5173").unwrap();
+
+        assert!(headers.get(header::AUTHORIZATION).is_none());
+        assert_eq!(headers[header::COOKIE], "theme=dark");
+        assert_eq!(headers[header::HOST], "127.0.0.1:5173");
+        assert_eq!(headers[header::ORIGIN], "http://127.0.0.1:5173");
+        assert_eq!(headers["x-forwarded-host"], "oqto.example.com");
+        assert_eq!(headers["x-forwarded-prefix"], "/api/dev-proxy/5173");
+    }
+
+    #[test]
+    fn response_headers_allow_framing() {
+        let mut headers = HeaderMap::new();
+        headers.insert(header::X_FRAME_OPTIONS, HeaderValue::from_static("DENY"));
+        headers.insert(
+            header::CONTENT_SECURITY_POLICY,
+            HeaderValue::from_static("default-src 'self'; frame-ancestors 'none'"),
+        );
+        headers.insert(
+            header::LOCATION,
+            HeaderValue::from_static("http://localhost:3000/login"),
+        );
+        headers.append(
+            header::SET_COOKIE,
+            HeaderValue::from_static("sid=1; Path=/; Domain=localhost; HttpOnly"),
+        );
+
+        rewrite_response_headers(&mut headers, "/api/dev-proxy/3000", 3000);
+
+        assert!(headers.get(header::X_FRAME_OPTIONS).is_none());
+        let policies: Vec<_> = headers
+            .get_all(header::CONTENT_SECURITY_POLICY)
+            .iter()
+            .collect();
+        assert_eq!(policies, ["default-src 'self'", PREVIEW_SANDBOX_POLICY]);
+        assert_eq!(headers[header::LOCATION], "/api/dev-proxy/3000/login");
+        assert_eq!(
+            headers[header::SET_COOKIE],
+            "sid=1; Path=/api/dev-proxy/3000; HttpOnly"
+        );
+    }
+
+    #[test]
+    fn response_headers_sandbox_every_page() {
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            header::CONTENT_SECURITY_POLICY,
+            HeaderValue::from_static("sandbox allow-scripts allow-same-origin; frame-ancestors *"),
+        );
+        rewrite_response_headers(&mut headers, "/api/sessions/s1/preview/3000", 3000);
+        let policies: Vec<_> = headers
+            .get_all(header::CONTENT_SECURITY_POLICY)
+            .iter()
+            .collect();
+        assert_eq!(
+            policies,
+            [
+                "sandbox allow-scripts allow-same-origin",
+                PREVIEW_SANDBOX_POLICY
+            ]
+        );
+
+        let mut headers = HeaderMap::new();
+        rewrite_response_headers(&mut headers, "/api/dev-proxy/3000", 3000);
+        assert_eq!(
+            headers[header::CONTENT_SECURITY_POLICY],
+            PREVIEW_SANDBOX_POLICY
+        );
+        assert!(!PREVIEW_SANDBOX_POLICY.contains("allow-same-origin"));
+    }
+
+    #[test]
+    fn location_outside_dev_server_is_kept() {
+        assert_eq!(
+            rewrite_location("https://github.com/login", "/api/dev-proxy/3000", 3000),
+            None
+        );
+        assert_eq!(
+            rewrite_location("/api/dev-proxy/3000/x", "/api/dev-proxy/3000", 3000),
+            None
+        );
+        assert_eq!(
+            rewrite_location("http://localhost:30001/", "/api/dev-proxy/3000", 3000),
+            None
+        );
+    }
+
+    #[test]
+    fn config_port_filter() {
+        let config = DevProxyConfig {
+            blocked_ports: vec![8080],
+            ..Default::default()
+        };
+        assert!(config.allows_port(5173));
+        assert!(!config.allows_port(8080));
+        assert!(!config.allows_port(22));
+    }
+}
@@ -5,13 +5,15 @@
 use axum::{
     body::Body,
     extract::{Path, Query, State, WebSocketUpgrade},
-    http::{Request, StatusCode},
+    http::{HeaderValue, Method, Request, StatusCode},
     response::{IntoResponse, Response},
 };
 use log::{error, info};
+use oqto_files::capability::{CapabilityScope, TOKEN_HEADER};
 
 use crate::auth::CurrentUser;
 use crate::session::SessionStatus;
+use crate::shared_workspace::SharePermission;
 
 use super::super::state::AppState;
 use super::builder::{
@@ -39,11 +41,12 @@ pub async fn proxy_voice_stt_ws(
     State(state): State<AppState>,
     ws: WebSocketUpgrade,
 ) -> Result<impl IntoResponse, StatusCode> {
-    if !state.voice.enabled {
+    let voice = state.voice();
+    if !voice.enabled {
         return Err(StatusCode::SERVICE_UNAVAILABLE);
     }
 
-    let target_url = state.voice.stt_url.clone();
+    let target_url = voice.stt_url;
 
     Ok(ws.on_upgrade(move |socket| async move {
         if let Err(e) = handle_voice_ws_proxy(socket, target_url).await {
@@ -57,11 +60,12 @@ pub async fn proxy_voice_tts_ws(
     State(state): State<AppState>,
     ws: WebSocketUpgrade,
 ) -> Result<impl IntoResponse, StatusCode> {
-    if !state.voice.enabled {
+    let voice = state.voice();
+    if !voice.enabled {
         return Err(StatusCode::SERVICE_UNAVAILABLE);
     }
 
-    let target_url = state.voice.tts_url.clone();
+    let target_url = voice.tts_url;
 
     Ok(ws.on_upgrade(move |socket| async move {
         if let Err(e) = handle_voice_ws_proxy(socket, target_url).await {
@@ -100,22 +104,78 @@ async fn proxy_fileserver_for_workspace_inner(
     user: CurrentUser,
     path: String,
     query: WorkspaceProxyQuery,
-    req: Request<Body>,
+    mut req: Request<Body>,
 ) -> Result<Response, StatusCode> {
-    let session = get_io_session_for_workspace(&state, &user, &query.workspace_path).await?;
+    let session = get_io_session_for_workspace(&state, user.id(), &query.workspace_path).await?;
     let directory_query = build_fileserver_query(&query.workspace_path, req.uri().query());
 
+    // Inside shared workspaces, reads need `file_read` and everything else
+    // `file_write`; members without `file_write` only get read-only tokens.
+    let mut scope = CapabilityScope::ReadWrite;
+    if let Some(sw_service) = state.shared_workspaces.as_ref() {
+        let permissions = sw_service
+            .permissions_for_path(&query.workspace_path, user.id())
+            .await
+            .map_err(|e| {
+                error!(
+                    "Failed to check share permissions for {}: {:?}",
+                    query.workspace_path, e
+                );
+                StatusCode::INTERNAL_SERVER_ERROR
+            })?;
+        if let Some(permissions) = permissions {
+            let required = if matches!(*req.method(), Method::GET | Method::HEAD) {
+                SharePermission::FileRead
+            } else {
+                SharePermission::FileWrite
+            };
+            if !permissions.allows(required) {
+                info!(
+                    "Fileserver request by {} in {} lacks '{}' permission",
+                    user.id(),
+                    query.workspace_path,
+                    required
+                );
+                return Err(StatusCode::FORBIDDEN);
+            }
+            if !permissions.allows(SharePermission::FileWrite) {
+                scope = CapabilityScope::Read;
+            }
+        }
+    }
+
+    // The fileserver only honours tokens the backend minted for this
+    // workspace; never forward one supplied by the client.
+    req.headers_mut().remove(TOKEN_HEADER);
+    let token = state
+        .sessions
+        .fileserver_token(&session.id, &query.workspace_path, scope)
+        .await
+        .map_err(|e| {
+            error!(
+                "Failed to mint fileserver token for session {}: {:?}",
+                session.id, e
+            );
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+    if let Some(token) = token {
+        let value = HeaderValue::from_str(&token).map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        req.headers_mut().insert(TOKEN_HEADER, value);
+    }
+
     let starting = matches!(session.status, SessionStatus::Starting);
-    proxy_http_request_with_query(
+    let meter = state.proxy_transfers.meter(user.id());
+    let response = proxy_http_request_with_query(
         state.http_client.clone(),
-        req,
+        meter.count_request(req),
         session.fileserver_port as u16,
         &path,
         starting,
         Some(&directory_query),
         state.max_proxy_body_bytes,
     )
-    .await
+    .await?;
+    Ok(meter.count_response(response))
 }
 
 // ============================================================================
@@ -157,15 +217,17 @@ async fn proxy_sldr_internal(
         StatusCode::SERVICE_UNAVAILABLE
     })?;
 
-    proxy_http_request(
+    let meter = state.proxy_transfers.meter(user.id());
+    let response = proxy_http_request(
         state.http_client.clone(),
-        req,
+        meter.count_request(req),
         port,
         &path,
         true,
         state.max_proxy_body_bytes,
     )
-    .await
+    .await?;
+    Ok(meter.count_response(response))
 }
 
 // ============================================================================
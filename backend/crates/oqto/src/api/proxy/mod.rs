@@ -1,14 +1,23 @@
 //! HTTP and WebSocket proxy for container services.
 //!
 //! This module provides generic proxy infrastructure and specific handlers
-//! for proxying requests to session services (fileserver, ttyd, etc.).
+//! for proxying requests to session services (fileserver, ttyd, etc.) and
+//! dev servers started by agents.
 
 pub mod builder;
+mod dev_server;
 mod handlers;
 mod mmry;
+mod ports;
+pub mod transfer;
 mod websocket;
 
 // Re-export public handler functions for routes
+pub use dev_server::{
+    DevProxyConfig, list_dev_server_ports, list_session_preview_ports, proxy_dev_server,
+    proxy_dev_server_root, proxy_session_preview, proxy_session_preview_root,
+    run_preview_port_watch,
+};
 pub use handlers::{
     proxy_browser_stream_ws, proxy_fileserver_for_workspace, proxy_fileserver_for_workspace_root,
     proxy_sldr, proxy_sldr_root, proxy_voice_stt_ws, proxy_voice_tts_ws,
@@ -17,6 +26,7 @@ pub use mmry::{
     proxy_mmry_add_for_workspace, proxy_mmry_list_for_workspace, proxy_mmry_memory_for_workspace,
     proxy_mmry_search_for_workspace,
 };
+pub use transfer::{ProxyTransfers, TransferMeter, UserTransfer};
 
 // Re-export tests module
 #[cfg(test)]
@@ -0,0 +1,182 @@
+//! Listening port detection for dev server previews.
+//!
+//! Reads the kernel socket tables (`/proc/net/tcp`, `/proc/net/tcp6`) to find
+//! TCP listeners reachable over loopback, together with the uid that owns them.
+
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+
+use serde::Serialize;
+
+/// Kernel socket state for `LISTEN`.
+const TCP_LISTEN: &str = "0A";
+
+/// A TCP listener that can be reached over loopback.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct ListeningPort {
+    pub port: u16,
+    /// Loopback address to dial (127.0.0.1 or ::1).
+    pub address: IpAddr,
+    /// Owner of the listening socket.
+    #[serde(skip)]
+    pub uid: u32,
+}
+
+impl ListeningPort {
+    /// `host:port` authority for connecting to the listener.
+    pub fn authority(&self) -> String {
+        match self.address {
+            IpAddr::V4(addr) => format!("{addr}:{}", self.port),
+            IpAddr::V6(addr) => format!("[{addr}]:{}", self.port),
+        }
+    }
+}
+
+/// Detect loopback-reachable TCP listeners, optionally restricted to one uid.
+///
+/// When a port listens on both IPv4 and IPv6 the IPv4 entry wins. Returns an
+/// empty list on platforms without procfs.
+pub fn detect_listening_ports(uid: Option<u32>) -> Vec<ListeningPort> {
+    let mut ports = Vec::new();
+    for path in ["/proc/net/tcp", "/proc/net/tcp6"] {
+        if let Ok(contents) = std::fs::read_to_string(path) {
+            ports.extend(parse_proc_net_tcp(&contents));
+        }
+    }
+
+    if let Some(uid) = uid {
+        ports.retain(|p| p.uid == uid);
+    }
+    ports.sort_by_key(|p| (p.port, p.address.is_ipv6()));
+    ports.dedup_by_key(|p| p.port);
+    ports
+}
+
+/// Parse the contents of `/proc/net/tcp` or `/proc/net/tcp6`.
+fn parse_proc_net_tcp(contents: &str) -> Vec<ListeningPort> {
+    parse_listeners(contents)
+        .into_iter()
+        .filter_map(|(bound, port, uid)| {
+            Some(ListeningPort {
+                port,
+                address: dial_address(bound)?,
+                uid,
+            })
+        })
+        .collect()
+}
+
+/// Ports listening on all interfaces in the socket tables of a container.
+///
+/// Only these are reachable through the container's address; listeners bound
+/// to loopback inside the container are not. Sorted and deduplicated.
+pub fn parse_exposed_ports(contents: &str) -> Vec<u16> {
+    let mut ports: Vec<u16> = parse_listeners(contents)
+        .into_iter()
+        .filter(|(bound, _, _)| match bound {
+            IpAddr::V4(addr) => addr.is_unspecified(),
+            IpAddr::V6(addr) => {
+                addr.is_unspecified() || addr.to_ipv4_mapped().is_some_and(|v4| v4.is_unspecified())
+            }
+        })
+        .map(|(_, port, _)| port)
+        .collect();
+    ports.sort_unstable();
+    ports.dedup();
+    ports
+}
+
+/// `(bound address, port, uid)` of every listening socket in a socket table.
+/// Header lines of concatenated tables are skipped.
+fn parse_listeners(contents: &str) -> Vec<(IpAddr, u16, u32)> {
+    contents
+        .lines()
+        .filter_map(|line| {
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            if fields.len() < 8 || fields[3] != TCP_LISTEN {
+                return None;
+            }
+            let (addr, port) = fields[1].split_once(':')?;
+            Some((
+                parse_hex_addr(addr)?,
+                u16::from_str_radix(port, 16).ok()?,
+                fields[7].parse().ok()?,
+            ))
+        })
+        .collect()
+}
+
+/// Kernel addresses are printed as native-endian 32-bit words in hex.
+fn parse_hex_addr(hex: &str) -> Option<IpAddr> {
+    match hex.len() {
+        8 => {
+            let word = u32::from_str_radix(hex, 16).ok()?;
+            Some(IpAddr::V4(Ipv4Addr::from(word.to_ne_bytes())))
+        }
+        32 => {
+            let mut octets = [0u8; 16];
+            for (i, chunk) in octets.chunks_mut(4).enumerate() {
+                let word = u32::from_str_radix(&hex[i * 8..i * 8 + 8], 16).ok()?;
+                chunk.copy_from_slice(&word.to_ne_bytes());
+            }
+            Some(IpAddr::V6(Ipv6Addr::from(octets)))
+        }
+        _ => None,
+    }
+}
+
+/// Map a bound address to the loopback address used to reach it, if any.
+fn dial_address(bound: IpAddr) -> Option<IpAddr> {
+    match bound {
+        IpAddr::V4(addr) if addr.is_unspecified() || addr.is_loopback() => {
+            Some(IpAddr::V4(Ipv4Addr::LOCALHOST))
+        }
+        IpAddr::V6(addr) => match addr.to_ipv4_mapped() {
+            Some(v4) => dial_address(IpAddr::V4(v4)),
+            None if addr.is_unspecified() || addr.is_loopback() => {
+                Some(IpAddr::V6(Ipv6Addr::LOCALHOST))
+            }
+            None => None,
+        },
+        IpAddr::V4(_) => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HEADER: &str = "  sl  local_address rem_address   st tx_queue rx_queue tr tm->when retrnsmt   uid  timeout inode\n";
+
+    #[test]
+    fn parses_ipv4_listeners() {
+        let contents = format!(
+            "{HEADER}   0: 0100007F:1435 00000000:0000 0A 00000000:00000000 00:00000000 00000000  1000        0 1 1\n   1: 00000000:0BB8 00000000:0000 0A 00000000:00000000 00:00000000 00000000     0        0 2 1\n   2: 0100007F:1435 0100007F:D2F0 01 00000000:00000000 00:00000000 00000000  1000        0 3 1\n   3: 0A00020F:1F90 00000000:0000 0A 00000000:00000000 00:00000000 00000000  1000        0 4 1\n"
+        );
+        let ports = parse_proc_net_tcp(&contents);
+        assert_eq!(ports.len(), 2);
+        assert_eq!(ports[0].port, 5173);
+        assert_eq!(ports[0].uid, 1000);
+        assert_eq!(ports[0].authority(), "127.0.0.1:5173");
+        assert_eq!(ports[1].port, 3000);
+        assert_eq!(ports[1].uid, 0);
+    }
+
+    #[test]
+    fn parses_ipv6_listeners() {
+        let contents = format!(
+            "{HEADER}   0: 00000000000000000000000001000000:1435 00000000000000000000000000000000:0000 0A 00000000:00000000 00:00000000 00000000  1000        0 1 1\n   1: 00000000000000000000000000000000:1F90 00000000000000000000000000000000:0000 0A 00000000:00000000 00:00000000 00000000  1000        0 2 1\n"
+        );
+        let ports = parse_proc_net_tcp(&contents);
+        assert_eq!(ports.len(), 2);
+        assert_eq!(ports[0].authority(), "[::1]:5173");
+        assert_eq!(ports[1].port, 8080);
+    }
+
+    #[test]
+    fn parses_exposed_container_ports() {
+        let contents = format!(
+            "{HEADER}   0: 0100007F:1435 00000000:0000 0A 00000000:00000000 00:00000000 00000000  1000        0 1 1\n   1: 00000000:0BB8 00000000:0000 0A 00000000:00000000 00:00000000 00000000     0        0 2 1\n{HEADER}   0: 00000000000000000000000000000000:0BB8 00000000000000000000000000000000:0000 0A 00000000:00000000 00:00000000 00000000  1000        0 3 1\n   1: 00000000000000000000000000000000:1F90 00000000000000000000000000000000:0000 0A 00000000:00000000 00:00000000 00000000  1000        0 4 1\n"
+        );
+        assert_eq!(parse_exposed_ports(&contents), vec![3000, 8080]);
+    }
+}
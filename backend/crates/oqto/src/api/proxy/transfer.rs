@@ -0,0 +1,163 @@
+//! Byte accounting for proxied request and response bodies.
+//!
+//! Bodies pass through the proxy as streams; [`CountedBody`] counts the data
+//! frames as they go by, so transfers are metered per user without holding
+//! them in memory. The totals are in-memory since startup and are the basis
+//! for transfer quotas.
+
+use std::pin::Pin;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::task::{Context, Poll};
+
+use axum::body::{Body, Bytes};
+use axum::http::{Request, Response};
+use dashmap::DashMap;
+use hyper::body::{Body as HttpBody, Frame, SizeHint};
+use serde::Serialize;
+
+/// Bytes moved through the proxy by one user.
+#[derive(Debug, Clone, Default)]
+struct TransferTotals {
+    uploaded: Arc<AtomicU64>,
+    downloaded: Arc<AtomicU64>,
+}
+
+/// Per-user proxy transfer totals.
+#[derive(Debug, Default)]
+pub struct ProxyTransfers {
+    users: DashMap<String, TransferTotals>,
+}
+
+/// A user's totals in the admin listing.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct UserTransfer {
+    pub user_id: String,
+    pub uploaded_bytes: u64,
+    pub downloaded_bytes: u64,
+}
+
+impl ProxyTransfers {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Meter for the requests of `user_id`.
+    pub fn meter(&self, user_id: &str) -> TransferMeter {
+        let totals = self
+            .users
+            .entry(user_id.to_string())
+            .or_default()
+            .value()
+            .clone();
+        TransferMeter { totals }
+    }
+
+    /// Totals of every user, largest download first.
+    pub fn snapshot(&self) -> Vec<UserTransfer> {
+        let mut transfers: Vec<UserTransfer> = self
+            .users
+            .iter()
+            .map(|entry| UserTransfer {
+                user_id: entry.key().clone(),
+                uploaded_bytes: entry.uploaded.load(Ordering::Relaxed),
+                downloaded_bytes: entry.downloaded.load(Ordering::Relaxed),
+            })
+            .collect();
+        transfers.sort_by(|a, b| {
+            b.downloaded_bytes
+                .cmp(&a.downloaded_bytes)
+                .then_with(|| a.user_id.cmp(&b.user_id))
+        });
+        transfers
+    }
+}
+
+/// Counts one user's proxied bodies.
+#[derive(Debug, Clone)]
+pub struct TransferMeter {
+    totals: TransferTotals,
+}
+
+impl TransferMeter {
+    /// Count the request body as it is forwarded upstream.
+    pub fn count_request(&self, req: Request<Body>) -> Request<Body> {
+        let counter = Arc::clone(&self.totals.uploaded);
+        req.map(|inner| Body::new(CountedBody { inner, counter }))
+    }
+
+    /// Count the response body as it is sent to the client.
+    pub fn count_response(&self, res: Response<Body>) -> Response<Body> {
+        let counter = Arc::clone(&self.totals.downloaded);
+        res.map(|inner| Body::new(CountedBody { inner, counter }))
+    }
+}
+
+/// A body that counts its data frames. Size hints and trailers are passed
+/// through unchanged.
+struct CountedBody {
+    inner: Body,
+    counter: Arc<AtomicU64>,
+}
+
+impl HttpBody for CountedBody {
+    type Data = Bytes;
+    type Error = axum::Error;
+
+    fn poll_frame(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Frame<Self::Data>, Self::Error>>> {
+        let poll = Pin::new(&mut self.inner).poll_frame(cx);
+        if let Poll::Ready(Some(Ok(frame))) = &poll
+            && let Some(data) = frame.data_ref()
+        {
+            self.counter.fetch_add(data.len() as u64, Ordering::Relaxed);
+        }
+        poll
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.inner.is_end_stream()
+    }
+
+    fn size_hint(&self) -> SizeHint {
+        self.inner.size_hint()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_meter_counts_streamed_bodies() {
+        let transfers = ProxyTransfers::new();
+        let meter = transfers.meter("alice");
+
+        let chunks = futures::stream::iter([
+            Ok::<_, std::io::Error>(Bytes::from_static(b"hello ")),
+            Ok(Bytes::from_static(b"world")),
+        ]);
+        let req = meter.count_request(Request::new(Body::from_stream(chunks)));
+        let body = axum::body::to_bytes(req.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        assert_eq!(&body[..], b"hello world");
+
+        let res = meter.count_response(Response::new(Body::from("abc")));
+        assert_eq!(res.body().size_hint().exact(), Some(3));
+        axum::body::to_bytes(res.into_body(), usize::MAX)
+            .await
+            .unwrap();
+
+        assert_eq!(
+            transfers.snapshot(),
+            vec![UserTransfer {
+                user_id: "alice".to_string(),
+                uploaded_bytes: 11,
+                downloaded_bytes: 3,
+            }]
+        );
+    }
+}
@@ -1,11 +1,12 @@
-//! WebSocket proxy utilities for voice and browser streaming.
+//! WebSocket proxy utilities for voice, browser streaming and dev servers.
 //!
 //! Provides bidirectional WebSocket relay functionality.
 
 use axum::extract::ws::WebSocket;
 use futures::{SinkExt, StreamExt};
 use log::{debug, info};
-use tokio_tungstenite::connect_async;
+use tokio::net::TcpStream;
+use tokio_tungstenite::{MaybeTlsStream, WebSocketStream, connect_async};
 
 /// Handle bidirectional WebSocket proxy to a voice service (STT/TTS).
 pub async fn handle_voice_ws_proxy(
@@ -202,3 +203,65 @@ pub async fn handle_browser_stream_proxy(
 
     Ok(())
 }
+
+/// Relay an accepted client WebSocket to an already connected dev server socket.
+///
+/// Close frames are forwarded with their codes so HMR clients can tell a
+/// server restart apart from a normal shutdown.
+pub async fn handle_dev_server_ws_proxy(
+    client_socket: WebSocket,
+    server_socket: WebSocketStream<MaybeTlsStream<TcpStream>>,
+) -> anyhow::Result<()> {
+    use axum::extract::ws::{CloseFrame as AxumCloseFrame, Message as AxumMessage};
+    use tokio_tungstenite::tungstenite::Message as TungsteniteMessage;
+    use tokio_tungstenite::tungstenite::protocol::CloseFrame as TungsteniteCloseFrame;
+
+    let (mut client_tx, mut client_rx) = client_socket.split();
+    let (mut server_tx, mut server_rx) = server_socket.split();
+
+    let client_to_server = async {
+        while let Some(msg) = client_rx.next().await {
+            let forward = match msg? {
+                AxumMessage::Text(text) => TungsteniteMessage::Text(text.to_string().into()),
+                AxumMessage::Binary(data) => TungsteniteMessage::Binary(data),
+                AxumMessage::Ping(data) => TungsteniteMessage::Ping(data),
+                AxumMessage::Pong(data) => TungsteniteMessage::Pong(data),
+                AxumMessage::Close(frame) => {
+                    TungsteniteMessage::Close(frame.map(|f| TungsteniteCloseFrame {
+                        code: f.code.into(),
+                        reason: f.reason.to_string().into(),
+                    }))
+                }
+            };
+            server_tx.send(forward).await?;
+        }
+        Ok::<(), anyhow::Error>(())
+    };
+
+    let server_to_client = async {
+        while let Some(msg) = server_rx.next().await {
+            let forward = match msg? {
+                TungsteniteMessage::Text(text) => AxumMessage::Text(text.to_string().into()),
+                TungsteniteMessage::Binary(data) => AxumMessage::Binary(data),
+                TungsteniteMessage::Ping(data) => AxumMessage::Ping(data),
+                TungsteniteMessage::Pong(data) => AxumMessage::Pong(data),
+                TungsteniteMessage::Close(frame) => {
+                    AxumMessage::Close(frame.map(|f| AxumCloseFrame {
+                        code: f.code.into(),
+                        reason: f.reason.to_string().into(),
+                    }))
+                }
+                TungsteniteMessage::Frame(_) => continue,
+            };
+            client_tx.send(forward).await?;
+        }
+        Ok::<(), anyhow::Error>(())
+    };
+
+    tokio::select! {
+        result = client_to_server => result?,
+        result = server_to_client => result?,
+    }
+
+    Ok(())
+}
@@ -0,0 +1,422 @@
+//! Request rate limiting.
+//!
+//! Token buckets per client IP and per user, with separate limits for auth
+//! endpoints, file uploads and agent messages on top of the general API
+//! limit. Requests over a limit get `429 Too Many Requests` with a
+//! `Retry-After` header. Agent messages sent over the multiplexed WebSocket
+//! are checked against the same `agent_message` limit.
+
+use std::net::IpAddr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+use axum::body::Body;
+use axum::extract::State;
+use axum::http::{HeaderValue, Method, Request, header};
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use dashmap::DashMap;
+use serde::{Deserialize, Serialize};
+use tracing::debug;
+
+use crate::auth::CurrentUser;
+use crate::net;
+
+use super::error::ApiError;
+use super::state::AppState;
+
+/// Buckets idle this long are full again and can be dropped.
+const IDLE_BUCKET: Duration = Duration::from_secs(600);
+/// Checks between sweeps of idle buckets.
+const SWEEP_EVERY: u64 = 4096;
+
+/// A token bucket: `burst` requests at once, refilled at `per_minute`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RateLimit {
+    /// Sustained requests per minute; 0 disables the limit.
+    pub per_minute: u32,
+    /// Requests allowed in a burst (at least 1).
+    pub burst: u32,
+}
+
+impl RateLimit {
+    pub const fn new(per_minute: u32, burst: u32) -> Self {
+        Self { per_minute, burst }
+    }
+
+    pub const fn unlimited() -> Self {
+        Self::new(0, 0)
+    }
+
+    fn enabled(&self) -> bool {
+        self.per_minute > 0
+    }
+
+    fn capacity(&self) -> f64 {
+        self.burst.max(1) as f64
+    }
+
+    fn refill_per_sec(&self) -> f64 {
+        self.per_minute as f64 / 60.0
+    }
+}
+
+impl Default for RateLimit {
+    fn default() -> Self {
+        Self::unlimited()
+    }
+}
+
+/// Limits of one class of requests.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RateLimitClass {
+    /// Per client IP.
+    pub per_ip: RateLimit,
+    /// Per authenticated user.
+    pub per_user: RateLimit,
+}
+
+/// `[rate_limit]` configuration.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RateLimitConfig {
+    pub enabled: bool,
+    /// Every API request.
+    pub general: RateLimitClass,
+    /// Login, registration and password changes.
+    pub auth: RateLimitClass,
+    /// Writes through the workspace file server.
+    pub upload: RateLimitClass,
+    /// Prompts, steering and follow-ups sent to agents.
+    pub agent_message: RateLimitClass,
+}
+
+impl Default for RateLimitConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            general: RateLimitClass {
+                per_ip: RateLimit::new(1200, 300),
+                per_user: RateLimit::new(600, 200),
+            },
+            auth: RateLimitClass {
+                per_ip: RateLimit::new(10, 5),
+                per_user: RateLimit::new(10, 5),
+            },
+            upload: RateLimitClass {
+                per_ip: RateLimit::new(120, 40),
+                per_user: RateLimit::new(60, 20),
+            },
+            agent_message: RateLimitClass {
+                per_ip: RateLimit::unlimited(),
+                per_user: RateLimit::new(30, 10),
+            },
+        }
+    }
+}
+
+/// Request class a limit applies to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Class {
+    General,
+    Auth,
+    Upload,
+    AgentMessage,
+}
+
+impl Class {
+    /// The specific class of an HTTP request, if it has one besides
+    /// [`Class::General`].
+    fn of(method: &Method, path: &str) -> Option<Self> {
+        let path = path.strip_prefix("/api").unwrap_or(path);
+        // Device sign-in polls every few seconds; the provider throttles it.
+        if path == "/auth/oidc/device/token" {
+            return None;
+        }
+        // Single sign-on runs through redirects, so through GETs.
+        if path.starts_with("/auth/oidc/") {
+            return Some(Self::Auth);
+        }
+        let writes = matches!(*method, Method::POST | Method::PUT | Method::PATCH);
+        if !writes {
+            return None;
+        }
+        if matches!(
+            path,
+            "/auth/login"
+                | "/auth/register"
+                | "/auth/dev-login"
+                | "/auth/change-password"
+                | "/auth/verify-email/resend"
+                | "/auth/passkeys/login/start"
+                | "/auth/passkeys/login/finish"
+        ) {
+            Some(Self::Auth)
+        } else if *method == Method::PATCH && path.starts_with("/workspace/files/uploads/") {
+            // Chunks of a resumable upload; starting it counted as the upload.
+            None
+        } else if path.starts_with("/workspace/files") {
+            Some(Self::Upload)
+        } else if path.starts_with("/delegate/prompt/") {
+            Some(Self::AgentMessage)
+        } else {
+            None
+        }
+    }
+
+    fn label(&self) -> &'static str {
+        match self {
+            Self::General => "API",
+            Self::Auth => "authentication",
+            Self::Upload => "upload",
+            Self::AgentMessage => "agent message",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+enum Subject {
+    Ip(IpAddr),
+    User(String),
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+/// Token buckets of all clients.
+pub struct RateLimiter {
+    config: RateLimitConfig,
+    buckets: DashMap<(Class, Subject), Bucket>,
+    checks: AtomicU64,
+}
+
+impl RateLimiter {
+    pub fn new(config: RateLimitConfig) -> Self {
+        Self {
+            config,
+            buckets: DashMap::new(),
+            checks: AtomicU64::new(0),
+        }
+    }
+
+    fn limits(&self, class: Class) -> &RateLimitClass {
+        match class {
+            Class::General => &self.config.general,
+            Class::Auth => &self.config.auth,
+            Class::Upload => &self.config.upload,
+            Class::AgentMessage => &self.config.agent_message,
+        }
+    }
+
+    /// Take a token for a request of `class`. Returns how long to wait
+    /// when a limit is exhausted.
+    pub fn check(
+        &self,
+        class: Class,
+        ip: Option<IpAddr>,
+        user_id: Option<&str>,
+    ) -> Result<(), Duration> {
+        if !self.config.enabled {
+            return Ok(());
+        }
+        self.maybe_sweep();
+        let limits = *self.limits(class);
+        let now = Instant::now();
+        let mut subjects = Vec::with_capacity(2);
+        if let Some(ip) = ip
+            && limits.per_ip.enabled()
+        {
+            subjects.push((Subject::Ip(ip), limits.per_ip));
+        }
+        if let Some(user_id) = user_id
+            && limits.per_user.enabled()
+        {
+            subjects.push((Subject::User(user_id.to_string()), limits.per_user));
+        }
+
+        // Check every bucket before taking from any, so a request rejected
+        // by one limit does not use up another.
+        let mut wait = Duration::ZERO;
+        for (subject, limit) in &subjects {
+            let tokens = self
+                .buckets
+                .get(&(class, subject.clone()))
+                .map(|bucket| refilled(*bucket, *limit, now))
+                .unwrap_or(limit.capacity());
+            if tokens < 1.0 {
+                let secs = (1.0 - tokens) / limit.refill_per_sec();
+                wait = wait.max(Duration::from_secs_f64(secs));
+            }
+        }
+        if !wait.is_zero() {
+            return Err(wait);
+        }
+        for (subject, limit) in subjects {
+            let mut bucket = self.buckets.entry((class, subject)).or_insert(Bucket {
+                tokens: limit.capacity(),
+                updated: now,
+            });
+            bucket.tokens = refilled(*bucket, limit, now) - 1.0;
+            bucket.updated = now;
+        }
+        Ok(())
+    }
+
+    fn maybe_sweep(&self) {
+        if self.checks.fetch_add(1, Ordering::Relaxed) % SWEEP_EVERY != 0 {
+            return;
+        }
+        let before = self.buckets.len();
+        self.buckets
+            .retain(|_, bucket| bucket.updated.elapsed() < IDLE_BUCKET);
+        debug!(
+            dropped = before.saturating_sub(self.buckets.len()),
+            "Swept idle rate limit buckets"
+        );
+    }
+}
+
+fn refilled(bucket: Bucket, limit: RateLimit, now: Instant) -> f64 {
+    let elapsed = now.saturating_duration_since(bucket.updated).as_secs_f64();
+    (bucket.tokens + elapsed * limit.refill_per_sec()).min(limit.capacity())
+}
+
+/// 429 response telling the client when to retry.
+pub fn too_many_requests(class: Class, wait: Duration) -> Response {
+    let retry_after = wait.as_secs_f64().ceil().max(1.0) as u64;
+    let mut response = ApiError::too_many_requests(format!(
+        "{} rate limit exceeded, retry in {retry_after}s",
+        class.label()
+    ))
+    .into_response();
+    response
+        .headers_mut()
+        .insert(header::RETRY_AFTER, HeaderValue::from(retry_after));
+    response
+}
+
+/// Apply the general limit and the limit of the request's class, if any.
+/// Runs after authentication on protected routes, so authenticated requests
+/// also count against their user.
+pub async fn rate_limit_middleware(
+    State(state): State<AppState>,
+    req: Request<Body>,
+    next: Next,
+) -> Response {
+    let Some(limiter) = state.rate_limiter.as_ref() else {
+        return next.run(req).await;
+    };
+    let ip = net::client_ip(req.extensions());
+    let user_id = req
+        .extensions()
+        .get::<CurrentUser>()
+        .map(|user| user.id().to_string());
+
+    let classes = [
+        Some(Class::General),
+        Class::of(req.method(), req.uri().path()),
+    ];
+    for class in classes.into_iter().flatten() {
+        if let Err(wait) = limiter.check(class, ip, user_id.as_deref()) {
+            debug!(
+                ip = ?ip,
+                user_id = ?user_id,
+                path = %req.uri().path(),
+                class = class.label(),
+                "Rate limited request"
+            );
+            return too_many_requests(class, wait);
+        }
+    }
+    next.run(req).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn limiter() -> RateLimiter {
+        RateLimiter::new(RateLimitConfig {
+            auth: RateLimitClass {
+                per_ip: RateLimit::new(60, 2),
+                per_user: RateLimit::unlimited(),
+            },
+            agent_message: RateLimitClass {
+                per_ip: RateLimit::unlimited(),
+                per_user: RateLimit::new(6, 1),
+            },
+            ..Default::default()
+        })
+    }
+
+    #[test]
+    fn test_bucket_allows_burst_then_waits() {
+        let limiter = limiter();
+        let ip: IpAddr = "203.0.113.7".parse().unwrap();
+        assert!(limiter.check(Class::Auth, Some(ip), None).is_ok());
+        assert!(limiter.check(Class::Auth, Some(ip), None).is_ok());
+        let wait = limiter.check(Class::Auth, Some(ip), None).unwrap_err();
+        assert!(wait <= Duration::from_secs(1));
+
+        // Other clients and classes have their own buckets.
+        let other: IpAddr = "203.0.113.8".parse().unwrap();
+        assert!(limiter.check(Class::Auth, Some(other), None).is_ok());
+        assert!(limiter.check(Class::Upload, Some(ip), None).is_ok());
+
+        assert!(limiter.check(Class::AgentMessage, None, Some("u1")).is_ok());
+        let wait = limiter
+            .check(Class::AgentMessage, None, Some("u1"))
+            .unwrap_err();
+        assert!(wait > Duration::from_secs(9) && wait <= Duration::from_secs(10));
+        assert!(limiter.check(Class::AgentMessage, None, Some("u2")).is_ok());
+    }
+
+    #[test]
+    fn test_classify_requests() {
+        assert_eq!(
+            Class::of(&Method::POST, "/api/auth/login"),
+            Some(Class::Auth)
+        );
+        assert_eq!(
+            Class::of(&Method::GET, "/api/auth/oidc/login"),
+            Some(Class::Auth)
+        );
+        assert_eq!(
+            Class::of(&Method::POST, "/api/auth/passkeys/login/finish"),
+            Some(Class::Auth)
+        );
+        assert_eq!(
+            Class::of(&Method::POST, "/api/auth/oidc/device/token"),
+            None
+        );
+        assert_eq!(
+            Class::of(&Method::PUT, "/workspace/files/src/main.rs"),
+            Some(Class::Upload)
+        );
+        assert_eq!(
+            Class::of(&Method::GET, "/workspace/files/src/main.rs"),
+            None
+        );
+        assert_eq!(
+            Class::of(&Method::POST, "/api/workspace/files/uploads"),
+            Some(Class::Upload)
+        );
+        assert_eq!(
+            Class::of(&Method::PATCH, "/api/workspace/files/uploads/ab12"),
+            None
+        );
+        assert_eq!(
+            Class::of(&Method::POST, "/delegate/prompt/ses_1"),
+            Some(Class::AgentMessage)
+        );
+
+        let response = too_many_requests(Class::Auth, Duration::from_millis(1500));
+        assert_eq!(response.status(), axum::http::StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(response.headers()[header::RETRY_AFTER], "2");
+    }
+}
@@ -0,0 +1,27 @@
+//! Permission resolution for authenticated requests.
+
+use axum::body::Body;
+use axum::extract::State;
+use axum::http::Request;
+use axum::middleware::Next;
+use axum::response::Response;
+
+use crate::auth::CurrentUser;
+
+use super::state::AppState;
+
+/// Attach the permissions of the current user to the request, for the
+/// `Access` extractor.
+pub async fn rbac_middleware(
+    State(state): State<AppState>,
+    mut req: Request<Body>,
+    next: Next,
+) -> Response {
+    if let Some(rbac) = state.rbac.as_ref()
+        && let Some(user) = req.extensions().get::<CurrentUser>().cloned()
+    {
+        let permissions = rbac.permissions_for(&user).await;
+        req.extensions_mut().insert(permissions);
+    }
+    next.run(req).await
+}
@@ -4,7 +4,7 @@ use axum::extract::DefaultBodyLimit;
 use axum::http::{HeaderValue, Method, header};
 use axum::{
     Router, middleware,
-    routing::{delete, get, patch, post, put},
+    routing::{any, delete, get, patch, post, put},
 };
 use tower_http::cors::{AllowOrigin, CorsLayer};
 use tower_http::set_header::SetResponseHeaderLayer;
@@ -19,6 +19,8 @@ use super::delegate as delegate_handlers;
 use super::handlers;
 use super::onboarding_handlers;
 use super::proxy;
+use super::rate_limit;
+use super::rbac;
 use super::state::AppState;
 use super::ui_control as ui_control_handlers;
 use super::ws;
@@ -72,6 +74,10 @@ fn create_router_with_config_and_auth(
     let auth_state = crate::auth::AuthMiddlewareState {
         auth: state.auth.clone(),
         api_keys: Some(state.api_keys.as_ref().clone()),
+        impersonations: state
+            .impersonations
+            .as_ref()
+            .map(|service| service.repo().clone()),
     };
 
     // Protected routes (require authentication)
@@ -98,6 +104,11 @@ fn create_router_with_config_and_auth(
         // Project management
         .route("/projects", get(handlers::list_workspace_dirs))
         .route("/projects/logo/{*path}", get(handlers::get_project_logo))
+        .route("/projects/cards", get(handlers::list_project_cards))
+        .route(
+            "/projects/cards/thumbnail",
+            get(handlers::get_project_thumbnail),
+        )
         .route(
             "/projects/locations",
             get(handlers::list_workspace_locations).post(handlers::upsert_workspace_location),
@@ -110,7 +121,30 @@ fn create_router_with_config_and_auth(
             "/projects/templates",
             get(handlers::list_project_templates).post(handlers::create_project_from_template),
         )
+        .route(
+            "/projects/from-template",
+            post(handlers::create_project_from_template),
+        )
+        .route("/templates/{name}/test", post(handlers::test_template))
+        .route(
+            "/templates/{name}/tests",
+            get(handlers::list_template_tests),
+        )
         .route("/feedback", post(handlers::create_feedback))
+        // Usage analytics
+        .route("/analytics/tools", get(handlers::get_tool_stats))
+        .route("/analytics/tags", get(handlers::get_tag_stats))
+        .route("/analytics/feedback", get(handlers::get_feedback_stats))
+        .route("/session-tags", get(handlers::list_session_tags))
+        .route("/usage/summary", get(handlers::get_usage_summary))
+        .route(
+            "/usage/sessions/{session_id}",
+            get(handlers::get_session_usage),
+        )
+        .route(
+            "/usage/sessions/{session_id}/budget",
+            get(handlers::get_session_budget),
+        )
         // Shared workspaces
         .route(
             "/shared-workspaces",
@@ -132,6 +166,10 @@ fn create_router_with_config_and_auth(
             patch(handlers::update_shared_workspace_member)
                 .delete(handlers::remove_shared_workspace_member),
         )
+        .route(
+            "/shared-workspaces/{workspace_id}/members/{user_id}/permissions",
+            put(handlers::update_shared_workspace_member_permissions),
+        )
         .route(
             "/shared-workspaces/{workspace_id}/workdirs",
             get(handlers::list_shared_workspace_workdirs)
@@ -146,6 +184,23 @@ fn create_router_with_config_and_auth(
             "/shared-workspaces/{workspace_id}/transfer-ownership",
             post(handlers::transfer_shared_workspace_ownership),
         )
+        // Delegated access to directories in other users' workspaces
+        .route(
+            "/workspace-access/grants",
+            get(handlers::list_workspace_access_grants).post(handlers::request_workspace_access),
+        )
+        .route(
+            "/workspace-access/grants/{grant_id}",
+            delete(handlers::revoke_workspace_access),
+        )
+        .route(
+            "/workspace-access/grants/{grant_id}/approve",
+            post(handlers::approve_workspace_access),
+        )
+        .route(
+            "/workspace-access/grants/{grant_id}/deny",
+            post(handlers::deny_workspace_access),
+        )
         // Session management
         .route("/sessions", get(handlers::list_sessions))
         .route("/sessions", post(handlers::create_session))
@@ -162,12 +217,193 @@ fn create_router_with_config_and_auth(
             "/sessions/{session_id}/activity",
             post(handlers::touch_session_activity),
         )
+        .route(
+            "/sessions/{session_id}/resources",
+            get(handlers::get_session_resources),
+        )
+        .route(
+            "/sessions/{session_id}/resources/sampling",
+            put(handlers::update_session_resource_sampling),
+        )
+        .route(
+            "/sessions/{session_id}/build-image",
+            post(handlers::build_session_image),
+        )
         .route("/sessions/{session_id}", delete(handlers::delete_session))
         .route("/sessions/{session_id}/stop", post(handlers::stop_session))
+        .route(
+            "/sessions/{session_id}/events/poll",
+            get(handlers::poll_session_events),
+        )
+        .route(
+            "/sessions/{session_id}/artifacts",
+            get(handlers::list_session_artifacts),
+        )
+        .route(
+            "/sessions/{session_id}/artifacts/{artifact_id}",
+            get(handlers::get_session_artifact),
+        )
+        .route(
+            "/sessions/{session_id}/artifacts/{artifact_id}/thumbnail",
+            get(handlers::get_session_artifact_thumbnail),
+        )
+        .route(
+            "/sessions/{session_id}/processes",
+            get(handlers::list_background_processes).post(handlers::start_background_process),
+        )
+        .route(
+            "/sessions/{session_id}/processes/{process_id}",
+            delete(handlers::stop_background_process),
+        )
+        .route(
+            "/sessions/{session_id}/processes/{process_id}/logs",
+            get(handlers::get_background_process_logs),
+        )
+        .route(
+            "/sessions/{session_id}/approvals",
+            get(handlers::list_session_approvals),
+        )
+        .route(
+            "/sessions/{session_id}/approvals/{approval_id}",
+            post(handlers::resolve_session_approval),
+        )
+        .route(
+            "/sessions/{session_id}/promote-memory",
+            post(handlers::promote_session_memory),
+        )
+        .route(
+            "/sessions/{session_id}/memory-suggestions",
+            post(handlers::suggest_session_memory),
+        )
+        .route(
+            "/memory-suggestions",
+            get(handlers::list_memory_suggestions),
+        )
+        .route(
+            "/memory-suggestions/{suggestion_id}/accept",
+            post(handlers::accept_memory_suggestion),
+        )
+        .route(
+            "/memory-suggestions/{suggestion_id}/reject",
+            post(handlers::reject_memory_suggestion),
+        )
+        .route(
+            "/sessions/{session_id}/outbox",
+            post(handlers::stage_outbox_message),
+        )
+        .route("/outbox", get(handlers::list_outbox_messages))
+        .route("/outbox/{message_id}", get(handlers::get_outbox_message))
+        .route(
+            "/outbox/{message_id}/approve",
+            post(handlers::approve_outbox_message),
+        )
+        .route(
+            "/outbox/{message_id}/reject",
+            post(handlers::reject_outbox_message),
+        )
+        .route(
+            "/sessions/{session_id}/bookmarks",
+            get(handlers::list_bookmarks).post(handlers::create_bookmark),
+        )
+        .route(
+            "/bookmarks/{bookmark_id}",
+            get(handlers::get_bookmark)
+                .patch(handlers::update_bookmark)
+                .delete(handlers::delete_bookmark),
+        )
+        .route(
+            "/sessions/{session_id}/annotations",
+            get(handlers::list_annotations),
+        )
+        .route(
+            "/sessions/{session_id}/messages/{message_id}/annotations",
+            post(handlers::create_annotation),
+        )
+        .route(
+            "/annotations/{annotation_id}",
+            delete(handlers::delete_annotation),
+        )
+        .route(
+            "/sessions/{session_id}/private-messages",
+            get(handlers::list_private_messages).post(handlers::mark_message_private),
+        )
+        .route(
+            "/private-messages/{private_message_id}",
+            delete(handlers::delete_private_message),
+        )
+        .route(
+            "/sessions/{session_id}/export",
+            get(handlers::export_session),
+        )
+        .route(
+            "/macros",
+            get(handlers::list_macros).post(handlers::create_macro),
+        )
+        .route(
+            "/macros/{macro_id}",
+            get(handlers::get_macro)
+                .put(handlers::update_macro)
+                .delete(handlers::delete_macro),
+        )
+        .route("/macros/{macro_id}/run", post(handlers::run_macro))
+        .route(
+            "/sessions/{session_id}/share",
+            post(handlers::share_session),
+        )
+        .route(
+            "/sessions/{session_id}/shares",
+            get(handlers::list_session_shares),
+        )
+        .route(
+            "/sessions/{session_id}/shares/{user_id}",
+            delete(handlers::revoke_session_share),
+        )
+        .route(
+            "/sessions/shared-with-me",
+            get(handlers::list_shared_with_me),
+        )
+        .route(
+            "/session-drafts",
+            get(handlers::list_session_drafts).post(handlers::create_session_draft),
+        )
+        .route(
+            "/session-drafts/shared-with-me",
+            get(handlers::list_session_drafts_shared_with_me),
+        )
+        .route(
+            "/session-drafts/{draft_id}",
+            get(handlers::get_session_draft)
+                .put(handlers::update_session_draft)
+                .delete(handlers::delete_session_draft),
+        )
+        .route(
+            "/session-drafts/{draft_id}/start",
+            post(handlers::start_session_draft),
+        )
+        .route(
+            "/session-drafts/{draft_id}/share",
+            post(handlers::share_session_draft),
+        )
+        .route(
+            "/session-drafts/{draft_id}/shares",
+            get(handlers::list_session_draft_shares),
+        )
+        .route(
+            "/session-drafts/{draft_id}/shares/{user_id}",
+            delete(handlers::revoke_session_draft_share),
+        )
         .route(
             "/sessions/{session_id}/resume",
             post(handlers::resume_session),
         )
+        .route(
+            "/sessions/{session_id}/clone",
+            post(handlers::clone_session),
+        )
+        .route(
+            "/sessions/{session_id}/setup",
+            get(handlers::get_session_setup),
+        )
         .route(
             "/sessions/{session_id}/update",
             get(handlers::check_session_update),
@@ -199,10 +435,88 @@ fn create_router_with_config_and_auth(
             "/workspace/sandbox",
             get(handlers::get_workspace_sandbox).patch(handlers::update_workspace_sandbox),
         )
+        .route(
+            "/workspace/encryption",
+            get(handlers::get_workspace_encryption).post(handlers::update_workspace_encryption),
+        )
         .route(
             "/workspace/pi-resources",
             get(handlers::get_workspace_pi_resources).post(handlers::apply_workspace_pi_resources),
         )
+        .route(
+            "/workspaces/from-archive",
+            post(handlers::create_workspace_from_archive),
+        )
+        // Dependency vulnerability scans
+        .route(
+            "/workspaces/vulnerabilities",
+            get(handlers::list_workspace_vulnerability_scans),
+        )
+        .route(
+            "/workspaces/vulnerabilities/scan",
+            post(handlers::scan_workspace_vulnerabilities),
+        )
+        .route(
+            "/workspaces/{id}/vulnerabilities",
+            get(handlers::get_workspace_vulnerabilities),
+        )
+        // Workspace file history (`{id}` is the URL-encoded workspace path)
+        .route(
+            "/workspaces/{id}/files/{*path}",
+            get(handlers::get_workspace_file),
+        )
+        .route(
+            "/workspaces/{id}/file-versions/{*path}",
+            get(handlers::list_workspace_file_versions),
+        )
+        // Workspace git repository (`{id}` is the URL-encoded workspace path)
+        .route(
+            "/workspaces/{id}/git/status",
+            get(handlers::get_workspace_git_status),
+        )
+        .route(
+            "/workspaces/{id}/git/diff",
+            get(handlers::get_workspace_git_diff),
+        )
+        .route(
+            "/workspaces/{id}/git/log",
+            get(handlers::get_workspace_git_log),
+        )
+        .route(
+            "/workspaces/{id}/git/branches",
+            get(handlers::list_workspace_git_branches).post(handlers::switch_workspace_git_branch),
+        )
+        .route(
+            "/workspaces/{id}/git/commit",
+            post(handlers::commit_workspace_git),
+        )
+        .route(
+            "/workspaces/{id}/git/push",
+            post(handlers::push_workspace_git),
+        )
+        // Read-only SQL over workspace data files
+        .route(
+            "/workspaces/{id}/query",
+            post(handlers::query_workspace_data),
+        )
+        .route(
+            "/git/credentials",
+            get(handlers::list_git_credentials).put(handlers::set_git_credential),
+        )
+        .route(
+            "/git/credentials/{host}",
+            delete(handlers::delete_git_credential),
+        )
+        .route(
+            "/secrets",
+            get(handlers::list_secrets).post(handlers::create_secret),
+        )
+        .route(
+            "/secrets/{id}",
+            get(handlers::get_secret)
+                .put(handlers::update_secret)
+                .delete(handlers::delete_secret),
+        )
         // Workspace file server proxy (binary previews/downloads)
         .route(
             "/workspace/files",
@@ -220,6 +534,27 @@ fn create_router_with_config_and_auth(
                 .delete(proxy::proxy_fileserver_for_workspace)
                 .patch(proxy::proxy_fileserver_for_workspace),
         )
+        // Dev server preview proxy (HTTP + HMR WebSocket)
+        .route("/dev-proxy/ports", get(proxy::list_dev_server_ports))
+        .route("/dev-proxy/{port}", any(proxy::proxy_dev_server_root))
+        .route("/dev-proxy/{port}/", any(proxy::proxy_dev_server_root))
+        .route("/dev-proxy/{port}/{*path}", any(proxy::proxy_dev_server))
+        .route(
+            "/sessions/{session_id}/preview",
+            get(proxy::list_session_preview_ports),
+        )
+        .route(
+            "/sessions/{session_id}/preview/{port}",
+            any(proxy::proxy_session_preview_root),
+        )
+        .route(
+            "/sessions/{session_id}/preview/{port}/",
+            any(proxy::proxy_session_preview_root),
+        )
+        .route(
+            "/sessions/{session_id}/preview/{port}/{*path}",
+            any(proxy::proxy_session_preview),
+        )
         // Workspace-based mmry routes (single-user mode)
         .route(
             "/workspace/memories",
@@ -238,7 +573,59 @@ fn create_router_with_config_and_auth(
         // User profile routes (authenticated users)
         .route("/me", get(handlers::get_me))
         .route("/me", put(handlers::update_me))
+        .route("/me/permissions", get(handlers::get_my_permissions))
+        .route(
+            "/me/environment/export",
+            get(handlers::export_my_environment),
+        )
+        .route(
+            "/me/environment/import",
+            post(handlers::import_my_environment),
+        )
+        .route(
+            "/me/backups",
+            get(handlers::list_workspace_backups).post(handlers::create_workspace_backup),
+        )
+        .route(
+            "/me/backups/{workspace}/restore",
+            post(handlers::restore_workspace_backup),
+        )
+        .route(
+            "/me/backups/{workspace}/{snapshot_id}",
+            get(handlers::get_workspace_backup).delete(handlers::delete_workspace_backup),
+        )
+        .route("/me/impersonations", get(handlers::list_my_impersonations))
+        .route(
+            "/me/impersonations/{id}/approve",
+            post(handlers::approve_impersonation),
+        )
+        .route(
+            "/me/impersonations/{id}/deny",
+            post(handlers::deny_impersonation),
+        )
+        .route(
+            "/me/impersonations/{id}/end",
+            post(handlers::end_my_impersonation),
+        )
+        .route(
+            "/me/impersonations/{id}/activity",
+            get(handlers::get_impersonation_activity),
+        )
         .route("/auth/change-password", post(handlers::change_password))
+        .route("/auth/passkeys", get(handlers::list_passkeys))
+        .route(
+            "/auth/passkeys/register/start",
+            post(handlers::start_passkey_registration),
+        )
+        .route(
+            "/auth/passkeys/register/finish",
+            post(handlers::finish_passkey_registration),
+        )
+        .route("/auth/passkeys/{id}", delete(handlers::delete_passkey))
+        .route(
+            "/auth/verify-email/resend",
+            post(handlers::resend_verification_email),
+        )
         // API keys
         .route(
             "/keys",
@@ -246,6 +633,15 @@ fn create_router_with_config_and_auth(
         )
         .route("/keys/{key_id}", delete(handlers::delete_api_key))
         .route("/keys/{key_id}/revoke", delete(handlers::revoke_api_key))
+        // Personal access tokens (the same API keys, revoked rather than deleted)
+        .route(
+            "/tokens",
+            get(handlers::list_api_keys).post(handlers::create_api_key),
+        )
+        .route(
+            "/tokens/{key_id}",
+            get(handlers::get_api_key).delete(handlers::revoke_api_key),
+        )
         // OAuth provider login (per-user)
         .route("/oauth/providers", get(handlers::oauth_providers))
         .route("/oauth/login/{provider}", post(handlers::oauth_login))
@@ -299,13 +695,71 @@ fn create_router_with_config_and_auth(
             "/admin/sessions/{session_id}",
             delete(handlers::admin_force_stop_session),
         )
+        .route(
+            "/admin/sessions/{session_id}/resource-limits",
+            put(handlers::admin_set_session_resource_limits),
+        )
         .route(
             "/admin/local/cleanup",
             post(handlers::admin_cleanup_local_sessions),
         )
+        .route(
+            "/admin/crash-bundles",
+            get(handlers::admin_list_crash_bundles),
+        )
+        .route(
+            "/admin/users/{user_id}/crash-bundles/{bundle_id}",
+            get(handlers::admin_download_crash_bundle),
+        )
         // Admin routes - stats
         .route("/admin/stats", get(handlers::get_admin_stats))
+        .route(
+            "/admin/analytics/tools",
+            get(handlers::admin_get_tool_stats),
+        )
+        .route("/admin/analytics/tags", get(handlers::admin_get_tag_stats))
+        .route(
+            "/admin/analytics/feedback",
+            get(handlers::admin_get_feedback_stats),
+        )
+        .route(
+            "/admin/usage/summary",
+            get(handlers::admin_get_usage_summary),
+        )
+        .route(
+            "/admin/budget/sessions",
+            get(handlers::admin_list_session_budgets),
+        )
+        .route(
+            "/admin/budget/sessions/{session_id}",
+            put(handlers::admin_set_session_budget),
+        )
+        .route(
+            "/admin/status/incidents",
+            get(handlers::admin_list_incidents).post(handlers::admin_create_incident),
+        )
+        .route(
+            "/admin/status/incidents/{incident_id}",
+            patch(handlers::admin_update_incident).delete(handlers::admin_delete_incident),
+        )
         .route("/admin/bus/stats", get(handlers::get_bus_stats))
+        .route("/admin/siem/health", get(handlers::get_siem_health))
+        .route("/admin/audit", get(handlers::admin_query_audit_log))
+        .route("/admin/overview", get(handlers::get_admin_overview))
+        .route("/admin/config/reload", post(handlers::admin_reload_config))
+        .route("/admin/lanes", get(handlers::get_priority_lanes))
+        .route("/admin/reconnect", get(handlers::get_reconnect_stats))
+        .route("/admin/runners", get(handlers::get_runner_hosts))
+        .route("/admin/webhooks", get(handlers::admin_list_webhooks))
+        .route(
+            "/admin/webhooks/deliveries",
+            get(handlers::admin_list_webhook_deliveries),
+        )
+        .route(
+            "/admin/webhooks/deliveries/{delivery_id}/retry",
+            post(handlers::admin_retry_webhook_delivery),
+        )
+        .route("/admin/proxy/transfers", get(handlers::get_proxy_transfers))
         .route("/admin/bus/publish", post(handlers::publish_bus_event))
         // Admin routes - user management
         .route("/admin/users", get(handlers::list_users))
@@ -315,6 +769,7 @@ fn create_router_with_config_and_auth(
             post(handlers::sync_user_configs),
         )
         .route("/admin/users/stats", get(handlers::get_user_stats))
+        .route("/admin/disk-usage", get(handlers::get_disk_usage))
         .route("/admin/metrics", get(handlers::admin_metrics_stream))
         .route("/admin/users/{user_id}", get(handlers::get_user))
         .route("/admin/users/{user_id}", put(handlers::update_user))
@@ -327,6 +782,58 @@ fn create_router_with_config_and_auth(
             "/admin/users/{user_id}/activate",
             post(handlers::activate_user),
         )
+        .route(
+            "/admin/users/{user_id}/quota",
+            put(handlers::set_user_quota),
+        )
+        .route(
+            "/admin/users/{user_id}/resource-limits",
+            get(handlers::get_user_resource_limits)
+                .put(handlers::set_user_resource_limits)
+                .delete(handlers::delete_user_resource_limits),
+        )
+        .route(
+            "/admin/users/{user_id}/roles",
+            get(handlers::get_user_roles).put(handlers::set_user_roles),
+        )
+        .route(
+            "/admin/users/{user_id}/environment/export",
+            get(handlers::admin_export_user_environment),
+        )
+        .route(
+            "/admin/users/{user_id}/environment/import",
+            post(handlers::admin_import_user_environment),
+        )
+        .route(
+            "/admin/backups/prune",
+            post(handlers::prune_workspace_backups),
+        )
+        .route(
+            "/admin/impersonations",
+            get(handlers::admin_list_impersonations).post(handlers::request_impersonation),
+        )
+        .route(
+            "/admin/impersonations/{id}/token",
+            post(handlers::admin_issue_impersonation_token),
+        )
+        .route(
+            "/admin/impersonations/{id}/end",
+            post(handlers::admin_end_impersonation),
+        )
+        // Admin routes - roles
+        .route(
+            "/admin/roles",
+            get(handlers::list_roles).post(handlers::create_role),
+        )
+        .route(
+            "/admin/roles/{name}",
+            put(handlers::update_role).delete(handlers::delete_role),
+        )
+        // Admin routes - templates
+        .route(
+            "/admin/templates/sync",
+            post(handlers::sync_project_templates),
+        )
         // Admin routes - invite code management
         .route("/admin/invite-codes", get(handlers::list_invite_codes))
         .route("/admin/invite-codes", post(handlers::create_invite_code))
@@ -338,6 +845,28 @@ fn create_router_with_config_and_auth(
             "/admin/invite-codes/stats",
             get(handlers::get_invite_code_stats),
         )
+        .route(
+            "/admin/invite-codes/import",
+            post(handlers::import_invite_codes),
+        )
+        .route(
+            "/admin/invite-codes/export",
+            get(handlers::export_invite_codes),
+        )
+        .route(
+            "/admin/invite-codes/revoke",
+            post(handlers::revoke_invite_codes_batch),
+        )
+        // Admin routes - open registration approval queue
+        .route("/admin/registrations", get(handlers::list_registrations))
+        .route(
+            "/admin/registrations/{user_id}/approve",
+            post(handlers::approve_registration),
+        )
+        .route(
+            "/admin/registrations/{user_id}/reject",
+            post(handlers::reject_registration),
+        )
         // EAVS / Model management
         .route("/admin/eavs/providers", get(handlers::list_eavs_providers))
         .route(
@@ -362,6 +891,10 @@ fn create_router_with_config_and_auth(
             "/admin/invite-codes/{code_id}/revoke",
             post(handlers::revoke_invite_code),
         )
+        .route(
+            "/admin/invite-codes/{code_id}/redemptions",
+            get(handlers::list_invite_code_redemptions),
+        )
         // Admin routes - shared workspace management
         .route(
             "/admin/shared-workspaces",
@@ -408,19 +941,67 @@ fn create_router_with_config_and_auth(
             "/settings",
             get(handlers::get_settings_values).patch(handlers::update_settings_values),
         )
+        .route(
+            "/settings/document",
+            get(handlers::get_settings_document).patch(handlers::patch_settings_document),
+        )
+        .route("/settings/history", get(handlers::get_settings_history))
         .route("/settings/reload", post(handlers::reload_settings))
+        .route(
+            "/settings/sync",
+            get(handlers::get_settings_sync).post(handlers::sync_settings),
+        )
         // Legacy Main Chat routes removed -- all communication now goes through
         // the multiplexed WebSocket (agent channel)
         // HSTRY (chat history) search routes
         .route("/search", get(handlers::search_sessions))
-        // Scheduler (skdlr) overview
+        .route("/history/search", get(handlers::search_history))
+        // Scheduler (skdlr) overview, run history and agent schedules
         .route("/scheduler/overview", get(handlers::scheduler_overview))
         .route("/scheduler/jobs/{name}", delete(handlers::scheduler_delete))
+        .route(
+            "/schedules",
+            get(handlers::list_agent_schedules).post(handlers::create_agent_schedule),
+        )
+        .route(
+            "/schedules/{name}",
+            get(handlers::get_agent_schedule)
+                .put(handlers::update_agent_schedule)
+                .delete(handlers::delete_agent_schedule),
+        )
+        .route("/schedules/{name}/run", post(handlers::run_agent_schedule))
+        .route(
+            "/schedules/{name}/runs",
+            get(handlers::list_schedule_runs).post(handlers::report_schedule_run),
+        )
+        // Inbound triggers (fired through the public /triggers/{token})
+        .route(
+            "/me/triggers",
+            get(handlers::list_triggers).post(handlers::create_trigger),
+        )
+        .route(
+            "/me/triggers/{name}",
+            get(handlers::get_trigger)
+                .put(handlers::update_trigger)
+                .delete(handlers::delete_trigger),
+        )
+        .route(
+            "/me/triggers/{name}/token",
+            post(handlers::rotate_trigger_token),
+        )
+        .route("/me/triggers/{name}/runs", get(handlers::list_trigger_runs))
         // RSS/Atom feed fetch proxy
         .route("/feeds/fetch", get(handlers::fetch_feed))
         // CodexBar usage (optional, requires codexbar on PATH)
         .route("/codexbar/usage", get(handlers::codexbar_usage))
+        // Optional subsystem states
+        .route("/meta/capabilities", get(handlers::capabilities))
+        .route("/meta/voice", get(handlers::voice_health))
         // TRX (issue tracking) now uses mux-only channel
+        .layer(middleware::from_fn_with_state(
+            state.clone(),
+            crate::capabilities::require_capabilities,
+        ))
         .with_state(state.clone());
 
     let protected_routes =
@@ -429,13 +1010,34 @@ fn create_router_with_config_and_auth(
     // Public routes (no authentication)
     let public_routes = Router::new()
         .route("/health", get(handlers::health))
+        .route("/status", get(handlers::status))
+        .route("/metrics", get(handlers::metrics))
         .route("/ws/debug", get(handlers::ws_debug))
         .route("/features", get(handlers::features))
         .route("/auth/login", post(handlers::login))
         .route("/auth/register", post(handlers::register))
+        .route("/auth/verify-email", get(handlers::verify_email))
         .route("/auth/logout", post(handlers::logout))
+        .route("/auth/oidc/login", get(handlers::oidc_login))
+        .route("/auth/oidc/callback", get(handlers::oidc_callback))
+        .route("/auth/oidc/device", post(handlers::oidc_device_start))
+        .route("/auth/oidc/device/token", post(handlers::oidc_device_token))
+        .route(
+            "/auth/passkeys/login/start",
+            post(handlers::start_passkey_login),
+        )
+        .route(
+            "/auth/passkeys/login/finish",
+            post(handlers::finish_passkey_login),
+        )
         // Keep dev_login for backwards compatibility
         .route("/auth/dev-login", post(handlers::dev_login))
+        // Inbound trigger webhooks; the token is the credential
+        .route("/triggers/{token}", post(handlers::fire_trigger))
+        .layer(middleware::from_fn_with_state(
+            state.clone(),
+            rate_limit::rate_limit_middleware,
+        ))
         .with_state(state.clone());
 
     // Delegation routes (localhost-only, no auth - used by Pi extension)
@@ -459,6 +1061,10 @@ fn create_router_with_config_and_auth(
             post(delegate_handlers::stop_session),
         )
         .route("/delegate/sessions", get(delegate_handlers::list_sessions))
+        .layer(middleware::from_fn_with_state(
+            state.clone(),
+            rate_limit::rate_limit_middleware,
+        ))
         .with_state(state.clone());
 
     // Test harness routes (dev mode only, no auth)
@@ -506,10 +1112,19 @@ fn apply_auth_layers(
     auth_state: crate::auth::AuthMiddlewareState,
     auth_mode: AuthMode,
 ) -> Router {
-    let router = router.layer(middleware::from_fn_with_state(
-        state,
-        audit::audit_middleware,
-    ));
+    let router = router
+        .layer(middleware::from_fn_with_state(
+            state.clone(),
+            rate_limit::rate_limit_middleware,
+        ))
+        .layer(middleware::from_fn_with_state(
+            state.clone(),
+            rbac::rbac_middleware,
+        ))
+        .layer(middleware::from_fn_with_state(
+            state,
+            audit::audit_middleware,
+        ));
 
     match auth_mode {
         AuthMode::Jwt => router.layer(middleware::from_fn_with_state(auth_state, auth_middleware)),
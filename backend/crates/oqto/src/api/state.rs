@@ -20,7 +20,7 @@ use crate::api_keys::ApiKeyRepository;
 use crate::auth::AuthState;
 use crate::invite::InviteCodeRepository;
 use crate::local::LinuxUsersConfig;
-use crate::onboarding::OnboardingService;
+use crate::onboarding::{BootstrapScript, OnboardingService};
 
 use crate::session::SessionService;
 use crate::session_target::SessionTargetRepository;
@@ -76,7 +76,7 @@ impl Default for MmryState {
 ///
 /// Frontend clients connect to STT/TTS through backplane WebSocket proxies.
 /// This state provides the upstream URLs and default settings.
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq)]
 pub struct VoiceState {
     /// Whether voice mode is enabled.
     pub enabled: bool,
@@ -107,7 +107,7 @@ pub struct VoiceState {
 }
 
 /// Per-visualizer voice settings.
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq)]
 pub struct VisualizerVoiceState {
     pub voice: String,
     pub speed: f32,
@@ -201,59 +201,57 @@ impl TemplatesState {
 }
 
 impl TemplatesState {
+    /// Pull the templates repo now. Returns false when there is nothing to
+    /// pull (no repo configured, a local repo, or not a git checkout).
+    pub async fn sync_now(&self) -> anyhow::Result<bool> {
+        let Some(repo_path) = self.repo_path.as_ref() else {
+            return Ok(false);
+        };
+        if self.repo_type == TemplatesRepoType::Local || !repo_path.join(".git").exists() {
+            return Ok(false);
+        }
+
+        let output = tokio::process::Command::new("git")
+            .arg("-C")
+            .arg(repo_path)
+            .arg("pull")
+            .arg("--ff-only")
+            .stdout(std::process::Stdio::piped())
+            .stderr(std::process::Stdio::piped())
+            .output()
+            .await?;
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            anyhow::bail!("git pull failed: {}", stderr.trim());
+        }
+        *self.last_sync.lock().await = Some(Instant::now());
+        Ok(true)
+    }
+
     /// Spawn a background task that periodically syncs the templates repo via
     /// `git pull`. This replaces the previous approach of blocking the request
     /// that triggered the sync.
     pub fn start_background_sync(&self) {
-        let repo_path = match self.repo_path.clone() {
-            Some(p) => p,
-            None => return,
-        };
-        if self.repo_type == TemplatesRepoType::Local {
+        if self.repo_path.is_none() || self.repo_type == TemplatesRepoType::Local {
             return;
         }
         if !self.sync_on_list {
             return;
         }
 
-        let interval = self.sync_interval;
-        let last_sync = Arc::clone(&self.last_sync);
-
+        let templates = self.clone();
         tokio::spawn(async move {
             loop {
                 // Wait for the configured interval before the first/next sync.
-                tokio::time::sleep(interval).await;
+                tokio::time::sleep(templates.sync_interval).await;
 
-                if !repo_path.join(".git").exists() {
-                    debug!(
+                match templates.sync_now().await {
+                    Ok(true) => debug!("Background templates sync completed"),
+                    Ok(false) => debug!(
                         "Templates repo at {:?} is not a git repo, skipping background sync",
-                        repo_path
-                    );
-                    continue;
-                }
-
-                let result = tokio::process::Command::new("git")
-                    .arg("-C")
-                    .arg(&repo_path)
-                    .arg("pull")
-                    .arg("--ff-only")
-                    .stdout(std::process::Stdio::piped())
-                    .stderr(std::process::Stdio::piped())
-                    .output()
-                    .await;
-
-                match result {
-                    Ok(output) if output.status.success() => {
-                        debug!("Background templates sync completed");
-                        *last_sync.lock().await = Some(Instant::now());
-                    }
-                    Ok(output) => {
-                        let stderr = String::from_utf8_lossy(&output.stderr);
-                        warn!("Background templates sync failed: {}", stderr.trim());
-                    }
-                    Err(e) => {
-                        warn!("Background templates sync error: {}", e);
-                    }
+                        templates.repo_path
+                    ),
+                    Err(e) => warn!("Background templates sync failed: {:#}", e),
                 }
             }
         });
@@ -289,8 +287,8 @@ pub struct AppState {
 
     /// Mmry (memory service) configuration.
     pub mmry: MmryState,
-    /// Voice mode configuration.
-    pub voice: VoiceState,
+    /// Voice mode configuration. Replaced when the config is reloaded.
+    pub voice: Arc<std::sync::RwLock<VoiceState>>,
     /// Session UX configuration.
     pub session_ui: SessionUiState,
     /// Project templates configuration.
@@ -309,12 +307,16 @@ pub struct AppState {
     pub onboarding: Option<Arc<OnboardingService>>,
     /// Onboarding templates service for Main Chat initialization.
     pub onboarding_templates: Option<Arc<OnboardingTemplatesService>>,
+    /// Admin bootstrap script run once in a user's first workspace.
+    pub bootstrap_script: Option<Arc<BootstrapScript>>,
     /// WebSocket hub for real-time communication.
     pub ws_hub: Arc<WsHub>,
     /// Pending A2UI blocking requests (request_id -> response channel).
     pub pending_a2ui_requests: PendingA2uiRequests,
-    /// Max proxy body size (bytes) for buffered proxy requests.
+    /// Max request body size (bytes) streamed through the proxy.
     pub max_proxy_body_bytes: usize,
+    /// Per-user bytes transferred through the HTTP proxies.
+    pub proxy_transfers: Arc<super::proxy::ProxyTransfers>,
     /// Linux user isolation configuration (for multi-user mode).
     pub linux_users: Option<LinuxUsersConfig>,
     /// Runner socket pattern for multi-user mode (e.g., "/run/oqto/runner-sockets/{user}/oqto-runner.sock").
@@ -323,8 +325,16 @@ pub struct AppState {
     pub session_targets: Arc<SessionTargetRepository>,
     /// Audit logger for user-facing events.
     pub audit_logger: Option<Arc<crate::audit::AuditLogger>>,
+    /// SIEM exporter fed by the audit logger (None if export is disabled).
+    pub siem: Option<Arc<crate::siem::SiemExporter>>,
     /// Feedback configuration.
     pub feedback: crate::feedback::FeedbackConfig,
+    /// Dev server preview proxy configuration.
+    pub dev_proxy: super::proxy::DevProxyConfig,
+    /// User environment export/import configuration.
+    pub user_env: crate::user_env::UserEnvConfig,
+    /// Workspace at-rest encryption configuration.
+    pub workspace_encryption: crate::workspace::encryption::WorkspaceEncryptionConfig,
     /// EAVS client for LLM proxy integration (user provisioning, model catalog).
     pub eavs_client: Option<Arc<crate::eavs::EavsClient>>,
     /// Paths to eavs config files (for admin provider management).
@@ -353,6 +363,98 @@ pub struct AppState {
     /// Path to a reference models.json to copy to new users when eavs is not configured.
     /// Typically the admin user's ~/.pi/agent/models.json.
     pub pi_models_template_path: Option<std::path::PathBuf>,
+    /// Tool usage statistics (None when disabled).
+    pub tool_usage: Option<Arc<crate::tool_usage::ToolUsageService>>,
+    /// Per-session and per-model spend attribution (None when disabled).
+    pub usage: Option<Arc<crate::eavs::UsageService>>,
+    /// Per-session spend budgets (None when disabled).
+    pub budget: Option<Arc<crate::eavs::BudgetService>>,
+    /// Database integrity status (degraded-mode notices).
+    pub db_health: Arc<crate::db::DbHealth>,
+    /// Enabled/disabled/degraded state of optional subsystems.
+    pub capabilities: Arc<crate::capabilities::CapabilityRegistry>,
+    /// Cached health of the voice services (None until the poller starts).
+    pub voice_health: Option<Arc<crate::voice_health::VoiceHealthMonitor>>,
+    /// Public status page service (None when disabled).
+    pub status: Option<Arc<crate::status::StatusService>>,
+    /// Delegated cross-user workspace access (None unless multi-user).
+    pub workspace_access: Option<Arc<crate::workspace_access::WorkspaceAccessService>>,
+    /// Harness crash bundles attached to sessions.
+    pub crash_bundles: Option<Arc<crate::crash_bundles::CrashBundleService>>,
+    /// Buffered event feeds for long-polling clients (None when disabled).
+    pub session_events: Option<Arc<crate::session_events::SessionEventFeeds>>,
+    /// Scheduler run history (None when disabled).
+    pub scheduler: Option<Arc<crate::scheduler::SchedulerService>>,
+    /// Federated runner hosts (None without `[[runners]]`).
+    pub runner_pool: Option<Arc<crate::runner::federation::RunnerPool>>,
+    /// Memory promotion and the suggestion review queue (None when disabled).
+    pub memory_promotion: Option<Arc<crate::memory_promotion::MemoryPromotionService>>,
+    /// Dependency vulnerability scans (None when disabled).
+    pub vuln_scans: Option<Arc<crate::vuln_scan::VulnScanService>>,
+    /// Approval queue for agent-composed outbound messages (None when disabled).
+    pub outbox: Option<Arc<crate::outbox::OutboxService>>,
+    /// Inbound webhook and email triggers (None when disabled).
+    pub triggers: Option<Arc<crate::triggers::TriggerService>>,
+    /// Outbound session lifecycle webhooks (None when none are configured).
+    pub webhooks: Option<Arc<crate::webhooks::WebhookService>>,
+    /// Open self-service registration (None unless `registration.open`).
+    pub registration: Option<Arc<crate::registration::RegistrationService>>,
+    /// Session timeline bookmarks.
+    pub bookmarks: Option<Arc<crate::bookmarks::BookmarkRepository>>,
+    /// Reactions, flags and notes on agent responses.
+    pub annotations: Option<Arc<crate::message_annotations::AnnotationRepository>>,
+    /// Messages and parts private to the session owner.
+    pub private_messages: Option<Arc<crate::private_messages::PrivateMessageRepository>>,
+    /// Recorded template smoke-check runs.
+    pub template_tests: Option<Arc<crate::template_tests::TemplateTestRepository>>,
+    /// Sessions shared with other users.
+    pub session_shares: Option<Arc<crate::session_shares::SessionShareRepository>>,
+    /// Sessions configured ahead of time and started later.
+    pub session_drafts: Option<Arc<crate::session_drafts::SessionDraftRepository>>,
+    /// Live resource usage of running sessions (None when disabled).
+    pub session_resources: Option<Arc<crate::session::SessionResourceMonitor>>,
+    /// Sessions with a container image build running.
+    pub image_builds: Arc<crate::session::ImageBuilds>,
+    /// Per-IP and per-user request rate limits (None when disabled).
+    pub rate_limiter: Option<Arc<super::rate_limit::RateLimiter>>,
+    /// Cached rollups for the admin dashboard.
+    pub admin_overview: Option<Arc<crate::admin_overview::AdminOverviewService>>,
+    /// Cached project card data for the project grid.
+    pub project_cards: Option<Arc<crate::project_cards::ProjectCardService>>,
+    /// Stored HTTPS credentials for pushing workspace repositories.
+    pub git_credentials: Option<Arc<crate::git_credentials::GitCredentialRepository>>,
+    /// Sealed agent secrets (None when `[secrets]` is disabled).
+    pub secrets: Option<Arc<crate::secrets::SecretStore>>,
+    /// Prompt drafts shared between a user's devices.
+    pub prompt_drafts: Option<Arc<crate::prompt_drafts::PromptDraftService>>,
+    /// User-defined macros (None when disabled).
+    pub macros: Option<Arc<crate::macros::MacroService>>,
+    /// Applies config.toml changes to running services.
+    pub config_reloader: Option<Arc<crate::config_reload::ConfigReloader>>,
+    /// Shared agent event streams with reconnect replay (None when disabled).
+    pub event_streams: Option<Arc<crate::ws::SessionStreams>>,
+    /// Reconnect hints and pacing of session resumes after restarts.
+    pub reconnect: Arc<crate::ws::ReconnectGovernor>,
+    /// Incremental workspace backups (None when disabled).
+    pub workspace_backups: Option<Arc<crate::workspace_backups::WorkspaceBackupService>>,
+    /// Admin impersonation of users (None when disabled).
+    pub impersonations: Option<Arc<crate::impersonation::ImpersonationService>>,
+    /// OpenID Connect sign-in (None when `[auth.oidc]` is disabled).
+    pub oidc: Option<Arc<crate::auth::OidcProvider>>,
+    /// Passkey sign-in (None when `[auth.passkeys]` is disabled).
+    pub passkeys: Option<Arc<crate::auth::PasskeyService>>,
+    /// Automatic session tagging (None when disabled).
+    pub session_tags: Option<Arc<crate::session_tags::SessionTagService>>,
+    /// Prometheus metrics endpoint (None when disabled).
+    pub metrics: Option<Arc<crate::observability::metrics::MetricsService>>,
+    /// Interactive/background lane tracking (None when disabled).
+    pub priority_lanes: Option<Arc<crate::priority_lanes::PriorityLanes>>,
+    /// Pushes Pi settings changes into running sessions.
+    pub settings_sync: Option<Arc<crate::settings::SettingsSync>>,
+    /// History of settings document patches.
+    pub settings_history: Option<Arc<crate::settings::SettingsHistory>>,
+    /// Role-based permissions of non-admin users.
+    pub rbac: Option<Arc<crate::auth::RbacService>>,
 }
 
 /// Paths to eavs configuration files for admin provider management.
@@ -390,7 +492,7 @@ impl AppState {
             auth,
             http_client,
             mmry,
-            voice,
+            voice: Arc::new(std::sync::RwLock::new(voice)),
             session_ui,
             templates,
             sldr_users: None,
@@ -400,14 +502,20 @@ impl AppState {
             settings_pi_models: None,
             onboarding: None,
             onboarding_templates: None,
+            bootstrap_script: None,
             ws_hub: Arc::new(WsHub::new()),
             pending_a2ui_requests: super::a2ui::new_pending_requests(),
             max_proxy_body_bytes,
+            proxy_transfers: Arc::new(super::proxy::ProxyTransfers::new()),
             linux_users: None,
             runner_socket_pattern: None,
             session_targets: Arc::new(session_targets),
             audit_logger: None,
+            siem: None,
             feedback: crate::feedback::FeedbackConfig::default(),
+            dev_proxy: super::proxy::DevProxyConfig::default(),
+            user_env: crate::user_env::UserEnvConfig::default(),
+            workspace_encryption: Default::default(),
             eavs_client: None,
             eavs_config: None,
             eavs_oauth_enabled: false,
@@ -424,9 +532,65 @@ impl AppState {
                 bus
             },
             user_plane_metrics: Arc::new(crate::user_plane::UserPlaneMetrics::default()),
+            tool_usage: None,
+            usage: None,
+            budget: None,
+            db_health: Arc::new(crate::db::DbHealth::default()),
+            capabilities: Arc::new(crate::capabilities::CapabilityRegistry::default()),
+            voice_health: None,
+            status: None,
+            workspace_access: None,
+            crash_bundles: None,
+            session_events: None,
+            scheduler: None,
+            runner_pool: None,
+            memory_promotion: None,
+            vuln_scans: None,
+            outbox: None,
+            triggers: None,
+            webhooks: None,
+            registration: None,
+            bookmarks: None,
+            annotations: None,
+            private_messages: None,
+            template_tests: None,
+            session_shares: None,
+            session_drafts: None,
+            session_resources: None,
+            image_builds: Arc::new(crate::session::ImageBuilds::new()),
+            prompt_drafts: None,
+            git_credentials: None,
+            secrets: None,
+            admin_overview: None,
+            project_cards: None,
+            rate_limiter: None,
+            macros: None,
+            config_reloader: None,
+            event_streams: None,
+            reconnect: Arc::new(crate::ws::ReconnectGovernor::new(
+                crate::ws::ReconnectConfig::default(),
+            )),
+            workspace_backups: None,
+            impersonations: None,
+            oidc: None,
+            passkeys: None,
+            session_tags: None,
+            metrics: None,
+            priority_lanes: None,
+            settings_sync: None,
+            settings_history: None,
+            rbac: None,
         }
     }
 
+    /// Current voice mode configuration.
+    pub fn voice(&self) -> VoiceState {
+        self.voice
+            .read()
+            .map(|voice| voice.clone())
+            .unwrap_or_default()
+    }
+
     pub fn with_eavs_config(mut self, paths: EavsConfigPaths) -> Self {
         self.eavs_config = Some(paths);
         self
@@ -449,6 +613,27 @@ impl AppState {
         self
     }
 
+    /// Set the dev server preview proxy configuration.
+    pub fn with_dev_proxy_config(mut self, config: super::proxy::DevProxyConfig) -> Self {
+        self.dev_proxy = config;
+        self
+    }
+
+    /// Set the user environment export/import configuration.
+    pub fn with_user_env_config(mut self, config: crate::user_env::UserEnvConfig) -> Self {
+        self.user_env = config;
+        self
+    }
+
+    /// Set the workspace encryption configuration.
+    pub fn with_workspace_encryption_config(
+        mut self,
+        config: crate::workspace::encryption::WorkspaceEncryptionConfig,
+    ) -> Self {
+        self.workspace_encryption = config;
+        self
+    }
+
     /// Set the oqto settings service.
     pub fn with_settings_oqto(mut self, service: SettingsService) -> Self {
         self.settings_oqto = Some(Arc::new(service));
@@ -499,12 +684,24 @@ impl AppState {
         self
     }
 
+    /// Set the first-workspace bootstrap script.
+    pub fn with_bootstrap_script(mut self, script: BootstrapScript) -> Self {
+        self.bootstrap_script = Some(Arc::new(script));
+        self
+    }
+
     /// Set the per-user sldr manager.
     pub fn with_sldr_users(mut self, manager: UserSldrManager) -> Self {
         self.sldr_users = Some(Arc::new(manager));
         self
     }
 
+    /// Set the SIEM exporter (for its health report).
+    pub fn with_siem(mut self, exporter: Arc<crate::siem::SiemExporter>) -> Self {
+        self.siem = Some(exporter);
+        self
+    }
+
     /// Set the audit logger for user-facing events.
     pub fn with_audit_logger(mut self, logger: Arc<crate::audit::AuditLogger>) -> Self {
         self.audit_logger = Some(logger);
@@ -534,6 +731,342 @@ impl AppState {
         self
     }
 
+    /// Share the database health record populated by startup checks.
+    pub fn with_db_health(mut self, health: Arc<crate::db::DbHealth>) -> Self {
+        self.db_health = health;
+        self
+    }
+
+    /// Share the subsystem capability registry populated at startup.
+    pub fn with_capabilities(
+        mut self,
+        registry: Arc<crate::capabilities::CapabilityRegistry>,
+    ) -> Self {
+        self.capabilities = registry;
+        self
+    }
+
+    /// Share the voice service health poller.
+    pub fn with_voice_health(
+        mut self,
+        monitor: Arc<crate::voice_health::VoiceHealthMonitor>,
+    ) -> Self {
+        self.voice_health = Some(monitor);
+        self
+    }
+
+    /// Set the tool usage statistics service.
+    pub fn with_tool_usage(mut self, service: Arc<crate::tool_usage::ToolUsageService>) -> Self {
+        self.tool_usage = Some(service);
+        self
+    }
+
+    /// Set the spend attribution service.
+    pub fn with_usage(mut self, service: Arc<crate::eavs::UsageService>) -> Self {
+        self.usage = Some(service);
+        self
+    }
+
+    /// Set the session budget service.
+    pub fn with_budget(mut self, service: Arc<crate::eavs::BudgetService>) -> Self {
+        self.budget = Some(service);
+        self
+    }
+
+    /// Set the public status page service.
+    pub fn with_status(mut self, service: Arc<crate::status::StatusService>) -> Self {
+        self.status = Some(service);
+        self
+    }
+
+    /// Set the delegated workspace access service.
+    pub fn with_workspace_access(
+        mut self,
+        service: Arc<crate::workspace_access::WorkspaceAccessService>,
+    ) -> Self {
+        self.workspace_access = Some(service);
+        self
+    }
+
+    /// Set the harness crash bundle service.
+    pub fn with_crash_bundles(
+        mut self,
+        service: Arc<crate::crash_bundles::CrashBundleService>,
+    ) -> Self {
+        self.crash_bundles = Some(service);
+        self
+    }
+
+    /// Set the long-poll event feeds.
+    pub fn with_session_events(
+        mut self,
+        feeds: Arc<crate::session_events::SessionEventFeeds>,
+    ) -> Self {
+        self.session_events = Some(feeds);
+        self
+    }
+
+    /// Set the scheduler run history service.
+    pub fn with_scheduler(mut self, service: Arc<crate::scheduler::SchedulerService>) -> Self {
+        self.scheduler = Some(service);
+        self
+    }
+
+    /// Set the federated runner hosts.
+    pub fn with_runner_pool(mut self, pool: Arc<crate::runner::federation::RunnerPool>) -> Self {
+        self.runner_pool = Some(pool);
+        self
+    }
+
+    /// Set the memory promotion service.
+    pub fn with_memory_promotion(
+        mut self,
+        service: Arc<crate::memory_promotion::MemoryPromotionService>,
+    ) -> Self {
+        self.memory_promotion = Some(service);
+        self
+    }
+
+    /// Set the dependency vulnerability scan service.
+    pub fn with_vuln_scans(mut self, service: Arc<crate::vuln_scan::VulnScanService>) -> Self {
+        self.vuln_scans = Some(service);
+        self
+    }
+
+    /// Set the outbound message approval service.
+    pub fn with_outbox(mut self, service: Arc<crate::outbox::OutboxService>) -> Self {
+        self.outbox = Some(service);
+        self
+    }
+
+    /// Set the inbound trigger service.
+    pub fn with_triggers(mut self, service: Arc<crate::triggers::TriggerService>) -> Self {
+        self.triggers = Some(service);
+        self
+    }
+
+    /// Set the outbound webhook service.
+    pub fn with_webhooks(mut self, service: Arc<crate::webhooks::WebhookService>) -> Self {
+        self.webhooks = Some(service);
+        self
+    }
+
+    /// Set the open registration service.
+    pub fn with_registration(
+        mut self,
+        service: Arc<crate::registration::RegistrationService>,
+    ) -> Self {
+        self.registration = Some(service);
+        self
+    }
+
+    /// Set the session bookmark repository.
+    pub fn with_bookmarks(mut self, repo: Arc<crate::bookmarks::BookmarkRepository>) -> Self {
+        self.bookmarks = Some(repo);
+        self
+    }
+
+    /// Set the session resource monitor.
+    pub fn with_session_resources(
+        mut self,
+        monitor: Arc<crate::session::SessionResourceMonitor>,
+    ) -> Self {
+        self.session_resources = Some(monitor);
+        self
+    }
+
+    /// Set the message annotation repository.
+    pub fn with_annotations(
+        mut self,
+        repo: Arc<crate::message_annotations::AnnotationRepository>,
+    ) -> Self {
+        self.annotations = Some(repo);
+        self
+    }
+
+    /// Set the private message repository.
+    pub fn with_private_messages(
+        mut self,
+        repo: Arc<crate::private_messages::PrivateMessageRepository>,
+    ) -> Self {
+        self.private_messages = Some(repo);
+        self
+    }
+
+    /// Set the template test run repository.
+    pub fn with_template_tests(
+        mut self,
+        repo: Arc<crate::template_tests::TemplateTestRepository>,
+    ) -> Self {
+        self.template_tests = Some(repo);
+        self
+    }
+
+    /// Set the session share repository.
+    pub fn with_session_shares(
+        mut self,
+        repo: Arc<crate::session_shares::SessionShareRepository>,
+    ) -> Self {
+        self.session_shares = Some(repo);
+        self
+    }
+
+    /// Set the session draft repository.
+    pub fn with_session_drafts(
+        mut self,
+        repo: Arc<crate::session_drafts::SessionDraftRepository>,
+    ) -> Self {
+        self.session_drafts = Some(repo);
+        self
+    }
+
+    /// Set the request rate limiter.
+    pub fn with_rate_limiter(mut self, limiter: Arc<super::rate_limit::RateLimiter>) -> Self {
+        self.rate_limiter = Some(limiter);
+        self
+    }
+
+    /// Set the admin dashboard rollup service.
+    pub fn with_admin_overview(
+        mut self,
+        service: Arc<crate::admin_overview::AdminOverviewService>,
+    ) -> Self {
+        self.admin_overview = Some(service);
+        self
+    }
+
+    /// Set the project card service.
+    pub fn with_project_cards(
+        mut self,
+        service: Arc<crate::project_cards::ProjectCardService>,
+    ) -> Self {
+        self.project_cards = Some(service);
+        self
+    }
+
+    /// Set the prompt draft service.
+    pub fn with_prompt_drafts(
+        mut self,
+        service: Arc<crate::prompt_drafts::PromptDraftService>,
+    ) -> Self {
+        self.prompt_drafts = Some(service);
+        self
+    }
+
+    /// Set the git credential store.
+    pub fn with_git_credentials(
+        mut self,
+        repo: Arc<crate::git_credentials::GitCredentialRepository>,
+    ) -> Self {
+        self.git_credentials = Some(repo);
+        self
+    }
+
+    /// Set the secret store.
+    pub fn with_secrets(mut self, store: Arc<crate::secrets::SecretStore>) -> Self {
+        self.secrets = Some(store);
+        self
+    }
+
+    /// Set the config reloader used by the admin reload route.
+    pub fn with_config_reloader(
+        mut self,
+        reloader: Arc<crate::config_reload::ConfigReloader>,
+    ) -> Self {
+        self.config_reloader = Some(reloader);
+        self
+    }
+
+    /// Set the user macro service.
+    pub fn with_macros(mut self, service: Arc<crate::macros::MacroService>) -> Self {
+        self.macros = Some(service);
+        self
+    }
+
+    /// Set the shared agent event streams used for reconnect replay.
+    pub fn with_event_streams(mut self, streams: Arc<crate::ws::SessionStreams>) -> Self {
+        self.event_streams = Some(streams);
+        self
+    }
+
+    /// Set the reconnect governor.
+    pub fn with_reconnect(mut self, governor: Arc<crate::ws::ReconnectGovernor>) -> Self {
+        self.reconnect = governor;
+        self
+    }
+
+    /// Set the workspace backup service.
+    pub fn with_workspace_backups(
+        mut self,
+        service: Arc<crate::workspace_backups::WorkspaceBackupService>,
+    ) -> Self {
+        self.workspace_backups = Some(service);
+        self
+    }
+
+    /// Set the impersonation service.
+    pub fn with_impersonations(
+        mut self,
+        service: Arc<crate::impersonation::ImpersonationService>,
+    ) -> Self {
+        self.impersonations = Some(service);
+        self
+    }
+
+    /// Set the OpenID Connect provider.
+    pub fn with_oidc(mut self, provider: Arc<crate::auth::OidcProvider>) -> Self {
+        self.oidc = Some(provider);
+        self
+    }
+
+    /// Set the passkey service.
+    pub fn with_passkeys(mut self, passkeys: Arc<crate::auth::PasskeyService>) -> Self {
+        self.passkeys = Some(passkeys);
+        self
+    }
+
+    /// Set the session tagging service.
+    pub fn with_session_tags(
+        mut self,
+        service: Arc<crate::session_tags::SessionTagService>,
+    ) -> Self {
+        self.session_tags = Some(service);
+        self
+    }
+
+    /// Set the Prometheus metrics service.
+    pub fn with_metrics(
+        mut self,
+        service: Arc<crate::observability::metrics::MetricsService>,
+    ) -> Self {
+        self.metrics = Some(service);
+        self
+    }
+
+    /// Set the priority lanes that throttle background jobs.
+    pub fn with_priority_lanes(mut self, lanes: Arc<crate::priority_lanes::PriorityLanes>) -> Self {
+        self.priority_lanes = Some(lanes);
+        self
+    }
+
+    /// Enable syncing Pi settings into running sessions.
+    pub fn with_settings_sync(mut self, sync: crate::settings::SettingsSync) -> Self {
+        self.settings_sync = Some(Arc::new(sync));
+        self
+    }
+
+    /// Set the settings patch history.
+    pub fn with_settings_history(mut self, history: crate::settings::SettingsHistory) -> Self {
+        self.settings_history = Some(Arc::new(history));
+        self
+    }
+
+    /// Resolve permissions through roles.
+    pub fn with_rbac(mut self, rbac: crate::auth::RbacService) -> Self {
+        self.rbac = Some(Arc::new(rbac));
+        self
+    }
+
     /// Set default Pi provider/model from config (used when eavs is not configured).
     pub fn with_pi_defaults(
         mut self,
@@ -564,3 +1097,37 @@ impl AppState {
             .is_some_and(|cfg| cfg.enabled && cfg.strict_identity)
     }
 }
+
+#[cfg(test)]
+impl AppState {
+    /// State over an in-memory database, with every optional service off.
+    pub(crate) async fn for_tests() -> Self {
+        let db = crate::db::Database::in_memory().await.unwrap();
+        let sessions = SessionService::new(
+            crate::session::SessionRepository::new(db.shared().clone()),
+            Arc::new(crate::container::ContainerRuntime::new()),
+            crate::session::SessionServiceConfig::default(),
+        );
+        Self::new(
+            sessions,
+            crate::user::UserService::new(crate::user::UserRepository::new(db.shared().clone())),
+            InviteCodeRepository::new(db.shared().clone()),
+            ApiKeyRepository::new(db.shared().clone()),
+            AuthState::new(crate::auth::AuthConfig {
+                dev_mode: true,
+                ..Default::default()
+            }),
+            MmryState::default(),
+            VoiceState::default(),
+            SessionUiState::default(),
+            TemplatesState::new(
+                None,
+                TemplatesRepoType::Local,
+                false,
+                Duration::from_secs(3600),
+            ),
+            SessionTargetRepository::new(db.pool().clone()),
+            1024 * 1024,
+        )
+    }
+}
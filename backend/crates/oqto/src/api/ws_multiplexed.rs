@@ -5,7 +5,15 @@
 //! - `files` - File operations (future)
 //! - `terminal` - Terminal I/O (future)
 //! - `hstry` - History queries (future)
-//! - `system` - System events (connection status, errors)
+//! - `system` - System events (connection status, errors) and socket auth
+//! - `draft` - Prompt drafts shared between the user's connections
+//!
+//! Socket auth: the token's `exp` is enforced for the lifetime of the socket.
+//! Clients get `auth.expiring` shortly before expiry and can send
+//! `auth.refresh` with a new token; otherwise the socket is closed. Sockets can
+//! be bound to specific sessions (via the `sessions` query parameter or
+//! `auth.bind_sessions`), and are closed as soon as the user's tokens are
+//! revoked.
 
 use std::collections::{HashMap, HashSet};
 use std::sync::Arc;
@@ -14,7 +22,7 @@ use std::time::{Duration, Instant};
 use axum::{
     extract::{
         Query, State, WebSocketUpgrade,
-        ws::{Message, WebSocket},
+        ws::{CloseFrame, Message, WebSocket},
     },
     response::Response,
 };
@@ -40,11 +48,15 @@ use crate::session::Session;
 use crate::session_target::{SessionTargetRecord, SessionTargetScope};
 use crate::user_plane::{MeteredUserPlane, RunnerUserPlane, UserPlane, UserPlanePath};
 use crate::ws::hub::WsHub;
+use crate::ws::reconnect::{ClientKind, WS_CLOSE_SERVICE_RESTART};
 use crate::ws::types::{WsCommand as LegacyWsCommand, WsEvent as LegacyHubEvent};
+use oqto_files::capability::{CapabilityScope, TOKEN_HEADER};
 use oqto_runner::client::{PiSubscriptionEvent, RunnerClient};
 use oqto_runner::protocol::{PiCreateSessionRequest, PiSessionConfig as RunnerPiSessionConfig};
 
 use super::error::ApiError;
+use super::proxy::builder::{build_fileserver_query, get_io_session_for_workspace};
+use super::rate_limit::Class;
 
 const RECENT_CLIENT_IDS_MAX_PER_SESSION: usize = 512;
 const RECENT_CLIENT_IDS_TTL: Duration = Duration::from_secs(3600);
@@ -66,11 +78,17 @@ static RECENT_CLIENT_IDS: Lazy<tokio::sync::RwLock<HashMap<String, ClientIdEntry
     Lazy::new(|| tokio::sync::RwLock::new(HashMap::new()));
 
 mod agent;
+mod auth;
+mod drafts;
 mod files;
 mod history;
+mod session_shares;
+mod share_permissions;
 mod system;
 mod terminal;
 
+pub(crate) use session_shares::detach_participant;
+
 fn normalized_client_id(client_id: Option<&str>) -> Option<&str> {
     let client_id = client_id?;
     if client_id.is_empty() {
@@ -289,6 +307,7 @@ pub enum Channel {
     Session,
     System,
     Bus,
+    Draft,
 }
 
 // ============================================================================
@@ -306,6 +325,30 @@ pub enum WsCommand {
     Trx(TrxWsCommand),
     Session(SessionWsCommand),
     Bus(crate::bus::BusCommand),
+    System(SystemWsCommand),
+    Draft(drafts::DraftWsCommand),
+}
+
+/// System channel commands.
+///
+/// Handled inline by the connection loop since they mutate socket auth state.
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type")]
+pub enum SystemWsCommand {
+    /// Replace the socket's token before it expires.
+    #[serde(rename = "auth.refresh")]
+    AuthRefresh {
+        #[serde(default)]
+        id: Option<String>,
+        token: String,
+    },
+    /// Restrict the socket to the given sessions.
+    #[serde(rename = "auth.bind_sessions")]
+    AuthBindSessions {
+        #[serde(default)]
+        id: Option<String>,
+        session_ids: Vec<String>,
+    },
 }
 
 /// Files channel commands.
@@ -575,6 +618,7 @@ pub enum WsEvent {
     Trx(TrxWsEvent),
     System(SystemWsEvent),
     Bus(crate::bus::BusWsEvent),
+    Draft(drafts::DraftWsEvent),
 }
 
 /// Files channel events (placeholder).
@@ -857,8 +901,10 @@ pub enum TrxWsEvent {
 #[derive(Debug, Clone, Serialize)]
 #[serde(tag = "type", rename_all = "snake_case")]
 pub enum SystemWsEvent {
-    /// Connection established
-    Connected,
+    /// Connection established, with the state of optional subsystems.
+    Connected {
+        capabilities: Vec<crate::capabilities::Capability>,
+    },
     /// General error. If this was caused by a specific command, includes correlation ID.
     Error {
         #[serde(skip_serializing_if = "Option::is_none")]
@@ -875,6 +921,99 @@ pub enum SystemWsEvent {
         #[serde(skip_serializing_if = "Option::is_none")]
         detail: Option<Value>,
     },
+    /// User-facing notification from a backend subsystem.
+    Notification {
+        level: String,
+        title: String,
+        message: String,
+        category: String,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        detail: Option<Value>,
+    },
+    /// Voice services became available or unavailable.
+    #[serde(rename = "voice.availability")]
+    VoiceAvailability {
+        available: bool,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        reason: Option<String>,
+    },
+    /// Ports a session's dev servers listen on changed.
+    #[serde(rename = "preview.ports")]
+    PreviewPorts { session_id: String, ports: Vec<u16> },
+    /// A new resource usage sample of a running session.
+    #[serde(rename = "session.resources")]
+    SessionResources {
+        session_id: String,
+        sample: crate::session::resources::ResourceSample,
+    },
+    /// A session moved to other ports after a port conflict.
+    #[serde(rename = "session.port_conflict")]
+    SessionPortConflict {
+        session_id: String,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        port: Option<u16>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        holder: Option<crate::session::PortHolder>,
+        previous_ports: [i64; 3],
+        ports: [i64; 3],
+        attempt: u32,
+    },
+    /// A message was annotated, or an annotation was removed.
+    #[serde(rename = "message.annotation")]
+    MessageAnnotation {
+        session_id: String,
+        message_id: String,
+        annotation: Value,
+        removed: bool,
+    },
+    /// A message or part was made private, or public again.
+    #[serde(rename = "message.private")]
+    MessagePrivate {
+        session_id: String,
+        message_id: String,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        part_id: Option<String>,
+        private: bool,
+    },
+    /// Socket auth state, sent after connect.
+    #[serde(rename = "auth.state")]
+    AuthState {
+        expires_at: i64,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        bound_sessions: Option<Vec<String>>,
+    },
+    /// Token expires soon; send `auth.refresh` to keep the socket open.
+    #[serde(rename = "auth.expiring")]
+    AuthExpiring { expires_at: i64 },
+    /// Token refreshed.
+    #[serde(rename = "auth.refreshed")]
+    AuthRefreshed {
+        #[serde(skip_serializing_if = "Option::is_none")]
+        id: Option<String>,
+        expires_at: i64,
+    },
+    /// Socket bound to sessions.
+    #[serde(rename = "auth.sessions_bound")]
+    AuthSessionsBound {
+        #[serde(skip_serializing_if = "Option::is_none")]
+        id: Option<String>,
+        session_ids: Vec<String>,
+    },
+    /// Token expired; the socket closes after this event.
+    #[serde(rename = "auth.expired")]
+    AuthExpired,
+    /// Credentials revoked; the socket closes after this event.
+    #[serde(rename = "auth.revoked")]
+    AuthRevoked { reason: String },
+    /// Too many sessions are resuming at once; `session.create` for
+    /// `session_id` failed and should be retried after `retry_after_ms`.
+    #[serde(rename = "replay.throttled")]
+    ReplayThrottled {
+        #[serde(skip_serializing_if = "Option::is_none")]
+        id: Option<String>,
+        session_id: String,
+        retry_after_ms: u64,
+    },
 }
 
 // ============================================================================
@@ -887,6 +1026,13 @@ pub struct WsMultiplexedQuery {
     /// Optional authentication token (for WebSocket auth)
     #[serde(default)]
     pub token: Option<String>,
+    /// Optional comma-separated session IDs to bind this socket to.
+    #[serde(default)]
+    pub sessions: Option<String>,
+    /// `dashboard` for overviews and monitors; their session resumes wait
+    /// behind those of interactive clients after a restart.
+    #[serde(default)]
+    pub client: ClientKind,
 }
 
 // ============================================================================
@@ -899,7 +1045,7 @@ pub struct WsMultiplexedQuery {
 pub async fn ws_multiplexed_handler(
     State(state): State<AppState>,
     user: CurrentUser,
-    Query(_query): Query<WsMultiplexedQuery>,
+    Query(query): Query<WsMultiplexedQuery>,
     ws: WebSocketUpgrade,
 ) -> Result<Response, ApiError> {
     info!(
@@ -909,11 +1055,18 @@ pub async fn ws_multiplexed_handler(
 
     let user_id = user.id().to_string();
     let is_admin = user.is_admin();
+    let ws_auth = auth::WsAuthState::new(
+        &user.claims,
+        auth::parse_bound_sessions(query.sessions.as_deref()),
+    );
+    let client = query.client;
 
     Ok(ws
         .max_message_size(256 * 1024 * 1024)
         .max_frame_size(256 * 1024 * 1024)
-        .on_upgrade(move |socket| handle_multiplexed_ws(socket, state, user_id, is_admin)))
+        .on_upgrade(move |socket| {
+            handle_multiplexed_ws(socket, state, user_id, is_admin, ws_auth, client)
+        }))
 }
 
 /// Create a runner client for a user if multi-user mode is enabled.
@@ -1019,18 +1172,40 @@ struct WsConnectionState {
     session_runner_overrides: HashMap<String, RunnerClient>,
     /// Bus subscriber ID for this connection.
     bus_subscriber_id: crate::bus::SubscriberId,
+    /// User owning this connection.
+    user_id: String,
+    /// Tool usage tracker fed from forwarded agent events.
+    tool_usage: Option<Arc<crate::tool_usage::ToolUsageService>>,
+    /// Spend attribution fed from forwarded assistant messages.
+    usage: Option<Arc<crate::eavs::UsageService>>,
+    /// Attaches runner crash bundles to sessions after fatal agent errors.
+    crash_bundles: Option<Arc<crate::crash_bundles::CrashBundleService>>,
+    /// Announces completed agent runs to the configured webhooks.
+    webhooks: Option<Arc<crate::webhooks::WebhookService>>,
+    /// Shared agent event streams; None subscribes to the runner directly.
+    event_streams: Option<Arc<crate::ws::SessionStreams>>,
+    /// What the socket is used for; orders session resumes after restarts.
+    client: ClientKind,
+    /// Admin acting as the user on this socket; recorded with every
+    /// audited command.
+    impersonation: Option<crate::auth::Actor>,
 }
 
 #[derive(Clone, Debug)]
 struct PiSessionMeta {
     scope: Option<String>,
     cwd: Option<std::path::PathBuf>,
+    /// Sandbox profile the session was created with, kept for restarts.
+    sandbox_profile: Option<String>,
 }
 
 struct TerminalSession {
     owner_user_id: String,
     session_id: String,
     workspace_path: Option<String>,
+    /// False for view-only terminals (no `terminal_control` permission in
+    /// the shared workspace when opened): input and resizes are rejected.
+    can_control: bool,
     command_tx: mpsc::UnboundedSender<TerminalSessionCommand>,
     task: tokio::task::JoinHandle<()>,
 }
@@ -1047,14 +1222,19 @@ async fn handle_multiplexed_ws(
     state: AppState,
     user_id: String,
     is_admin: bool,
+    mut ws_auth: auth::WsAuthState,
+    client: ClientKind,
 ) {
+    let _connection = crate::observability::metrics::metrics().ws_connected();
     let (mut ws_sender, mut ws_receiver) = socket.split();
 
     // Create channel for forwarding events to WebSocket
     let (event_tx, mut event_rx) = mpsc::unbounded_channel::<WsEvent>();
 
     // Send connected event
-    let connected_event = WsEvent::System(SystemWsEvent::Connected);
+    let connected_event = WsEvent::System(SystemWsEvent::Connected {
+        capabilities: state.capabilities.snapshot(),
+    });
     if let Ok(json) = serde_json::to_string(&connected_event)
         && ws_sender.send(Message::Text(json.into())).await.is_err()
     {
@@ -1063,6 +1243,13 @@ async fn handle_multiplexed_ws(
 
     info!("Multiplexed WebSocket connected for user {}", user_id);
 
+    let _ = event_tx.send(WsEvent::System(SystemWsEvent::AuthState {
+        expires_at: ws_auth.expires_at(),
+        bound_sessions: ws_auth.bound_sessions(),
+    }));
+    let mut revocations = state.auth.subscribe_revocations();
+    let mut shutdown = state.reconnect.subscribe_shutdown();
+
     // Create connection state
     let conn_state = Arc::new(tokio::sync::Mutex::new(WsConnectionState {
         subscribed_sessions: HashSet::new(),
@@ -1075,6 +1262,14 @@ async fn handle_multiplexed_ws(
         file_watchers: HashMap::new(),
         session_runner_overrides: HashMap::new(),
         bus_subscriber_id: 0, // Set after bus registration
+        user_id: user_id.clone(),
+        tool_usage: state.tool_usage.clone(),
+        usage: state.usage.clone(),
+        crash_bundles: state.crash_bundles.clone(),
+        webhooks: state.webhooks.clone(),
+        event_streams: state.event_streams.clone(),
+        client,
+        impersonation: ws_auth.impersonation().cloned(),
     }));
 
     // Register this connection with the legacy WS hub only for non-agent
@@ -1102,6 +1297,77 @@ async fn handle_multiplexed_ws(
                     change_type: change,
                     detail,
                 })),
+                LegacyHubEvent::Notification {
+                    level,
+                    title,
+                    message,
+                    category,
+                    detail,
+                } => Some(WsEvent::System(SystemWsEvent::Notification {
+                    level,
+                    title,
+                    message,
+                    category,
+                    detail,
+                })),
+                LegacyHubEvent::VoiceAvailability { available, reason } => {
+                    Some(WsEvent::System(SystemWsEvent::VoiceAvailability {
+                        available,
+                        reason,
+                    }))
+                }
+                LegacyHubEvent::PreviewPorts { session_id, ports } => {
+                    Some(WsEvent::System(SystemWsEvent::PreviewPorts {
+                        session_id,
+                        ports,
+                    }))
+                }
+                LegacyHubEvent::SessionResources { session_id, sample } => {
+                    Some(WsEvent::System(SystemWsEvent::SessionResources {
+                        session_id,
+                        sample,
+                    }))
+                }
+                LegacyHubEvent::SessionPortConflict {
+                    session_id,
+                    port,
+                    holder,
+                    previous_ports,
+                    ports,
+                    attempt,
+                } => Some(WsEvent::System(SystemWsEvent::SessionPortConflict {
+                    session_id,
+                    port,
+                    holder,
+                    previous_ports,
+                    ports,
+                    attempt,
+                })),
+                LegacyHubEvent::MessageAnnotation {
+                    session_id,
+                    message_id,
+                    annotation,
+                    removed,
+                } => Some(WsEvent::System(SystemWsEvent::MessageAnnotation {
+                    session_id,
+                    message_id,
+                    annotation,
+                    removed,
+                })),
+                LegacyHubEvent::MessagePrivate {
+                    session_id,
+                    message_id,
+                    part_id,
+                    private,
+                } => Some(WsEvent::System(SystemWsEvent::MessagePrivate {
+                    session_id,
+                    message_id,
+                    part_id,
+                    private,
+                })),
+                LegacyHubEvent::AgentEvent { event, .. } => serde_json::from_value(event)
+                    .ok()
+                    .map(|event| WsEvent::Agent(Box::new(event))),
                 _ => None,
             }
         };
@@ -1156,13 +1422,37 @@ async fn handle_multiplexed_ws(
             &state, &user_id,
         )));
 
-    // Spawn task to forward events from channel to WebSocket
-    let event_writer = tokio::spawn(async move {
-        while let Some(event) = event_rx.recv().await {
-            if let Ok(json) = serde_json::to_string(&event)
-                && ws_sender.send(Message::Text(json.into())).await.is_err()
-            {
-                break;
+    // Spawn task to forward events from channel to WebSocket.
+    // A close frame sent on `close_tx` flushes queued events first, so the
+    // client sees why the socket is going away.
+    let (close_tx, mut close_rx) = oneshot::channel::<CloseFrame>();
+    let mut event_writer = tokio::spawn(async move {
+        loop {
+            tokio::select! {
+                biased;
+                maybe_event = event_rx.recv() => {
+                    let Some(event) = maybe_event else {
+                        break;
+                    };
+                    if let Ok(json) = serde_json::to_string(&event)
+                        && ws_sender.send(Message::Text(json.into())).await.is_err()
+                    {
+                        break;
+                    }
+                }
+                frame = &mut close_rx => {
+                    while let Ok(event) = event_rx.try_recv() {
+                        if let Ok(json) = serde_json::to_string(&event)
+                            && ws_sender.send(Message::Text(json.into())).await.is_err()
+                        {
+                            return;
+                        }
+                    }
+                    if let Ok(frame) = frame {
+                        let _ = ws_sender.send(Message::Close(Some(frame))).await;
+                    }
+                    break;
+                }
             }
         }
     });
@@ -1221,15 +1511,102 @@ async fn handle_multiplexed_ws(
     );
 
     // Handle incoming messages
+    let mut close_frame: Option<CloseFrame> = None;
     loop {
+        let (auth_wait, auth_deadline) = ws_auth.next_deadline(Utc::now().timestamp());
         tokio::select! {
+            _ = tokio::time::sleep(auth_wait) => {
+                match auth_deadline {
+                    auth::AuthDeadline::Warn => {
+                        ws_auth.mark_expiry_warned();
+                        let _ = event_tx.send(WsEvent::System(SystemWsEvent::AuthExpiring {
+                            expires_at: ws_auth.expires_at(),
+                        }));
+                    }
+                    auth::AuthDeadline::Expire => {
+                        info!("Multiplexed WebSocket token expired for user {}", user_id);
+                        let _ = event_tx.send(WsEvent::System(SystemWsEvent::AuthExpired));
+                        close_frame = Some(CloseFrame {
+                            code: auth::WS_CLOSE_TOKEN_EXPIRED,
+                            reason: "token expired".into(),
+                        });
+                        break;
+                    }
+                }
+            }
+            revocation = revocations.recv() => {
+                match revocation {
+                    Ok(revocation) if ws_auth.is_revoked_by(&revocation, &user_id) => {
+                        info!(
+                            "Multiplexed WebSocket revoked for user {}: {}",
+                            user_id, revocation.reason
+                        );
+                        let _ = event_tx.send(WsEvent::System(SystemWsEvent::AuthRevoked {
+                            reason: revocation.reason.clone(),
+                        }));
+                        close_frame = Some(CloseFrame {
+                            code: auth::WS_CLOSE_TOKEN_REVOKED,
+                            reason: revocation.reason.into(),
+                        });
+                        break;
+                    }
+                    Ok(_) => {}
+                    Err(tokio::sync::broadcast::error::RecvError::Lagged(n)) => {
+                        // A missed notice cannot be proven irrelevant; fail closed.
+                        warn!(missed = n, user_id = %user_id, "revocation listener lagged");
+                        let _ = event_tx.send(WsEvent::System(SystemWsEvent::AuthRevoked {
+                            reason: "revocation state unknown".to_string(),
+                        }));
+                        close_frame = Some(CloseFrame {
+                            code: auth::WS_CLOSE_TOKEN_REVOKED,
+                            reason: "revocation state unknown".into(),
+                        });
+                        break;
+                    }
+                    Err(tokio::sync::broadcast::error::RecvError::Closed) => break,
+                }
+            }
+            Ok(_) = shutdown.wait_for(|stopping| *stopping) => {
+                // Spread the reconnects out instead of having every client
+                // come back the moment the server is up again.
+                close_frame = Some(CloseFrame {
+                    code: WS_CLOSE_SERVICE_RESTART,
+                    reason: state.reconnect.restart_reason(client).into(),
+                });
+                break;
+            }
             msg = ws_receiver.next() => {
                 match msg {
                     Some(Ok(Message::Text(text))) => {
                         match serde_json::from_str::<WsCommand>(&text) {
+                            Ok(WsCommand::System(system_cmd)) => {
+                                let event = handle_system_auth_command(
+                                    system_cmd,
+                                    &user_id,
+                                    &state,
+                                    &mut ws_auth,
+                                )
+                                .await;
+                                let _ = event_tx.send(event);
+                            }
                             Ok(cmd) => {
                                 debug!("Received WS command: {:?}", cmd);
 
+                                let (_, session_id, _) = ws_command_summary(&cmd);
+                                if !ws_auth.permits_session(session_id.as_deref()) {
+                                    warn!(
+                                        user_id = %user_id,
+                                        session_id = ?session_id,
+                                        "Rejected WS command for session outside socket binding"
+                                    );
+                                    let _ = event_tx.send(WsEvent::System(SystemWsEvent::Error {
+                                        id: ws_command_id(&cmd),
+                                        error: "Access denied: socket is not bound to this session"
+                                            .to_string(),
+                                    }));
+                                    continue;
+                                }
+
                                 let target_tx = match &cmd {
                                     WsCommand::Agent(_) => &agent_cmd_tx,
                                     WsCommand::Files(_) => &files_cmd_tx,
@@ -1279,6 +1656,11 @@ async fn handle_multiplexed_ws(
     }
 
     // Cleanup
+    if let Some(frame) = close_frame
+        && close_tx.send(frame).is_ok()
+    {
+        let _ = tokio::time::timeout(Duration::from_secs(1), &mut event_writer).await;
+    }
     event_writer.abort();
     hub_forwarder.abort();
     bus_forwarder.abort();
@@ -1311,6 +1693,75 @@ async fn handle_multiplexed_ws(
     info!("Multiplexed WebSocket closed for user {}", user_id);
 }
 
+/// Wait for a turn to resume `session_id` with replay. A caller turned
+/// away gets `replay.throttled` with a retry hint and the error for the
+/// command's response.
+async fn admit_replay(
+    state: &AppState,
+    conn_state: &Arc<tokio::sync::Mutex<WsConnectionState>>,
+    id: Option<&str>,
+    session_id: &str,
+) -> Result<(), String> {
+    let (client, event_tx) = {
+        let guard = conn_state.lock().await;
+        (guard.client, guard.event_tx.clone())
+    };
+    let Err(busy) = state.reconnect.admit(client).await else {
+        return Ok(());
+    };
+    warn!(
+        session_id = %session_id,
+        client = ?client,
+        retry_after_ms = busy.retry_after_ms,
+        "Session resume throttled"
+    );
+    let _ = event_tx.send(WsEvent::System(SystemWsEvent::ReplayThrottled {
+        id: id.map(str::to_string),
+        session_id: session_id.to_string(),
+        retry_after_ms: busy.retry_after_ms,
+    }));
+    Err(format!(
+        "Server busy: too many sessions resuming, retry in {} ms",
+        busy.retry_after_ms
+    ))
+}
+
+/// Handle a system channel auth command against the socket's auth state.
+async fn handle_system_auth_command(
+    cmd: SystemWsCommand,
+    user_id: &str,
+    state: &AppState,
+    ws_auth: &mut auth::WsAuthState,
+) -> WsEvent {
+    match cmd {
+        SystemWsCommand::AuthRefresh { id, token } => {
+            let result = match auth::validate_refresh_token(state, &token).await {
+                Ok(claims) => ws_auth.refresh(&claims, user_id),
+                Err(e) => Err(format!("Token refresh failed: {e}")),
+            };
+            match result {
+                Ok(()) => {
+                    debug!("Refreshed WebSocket token for user {}", user_id);
+                    WsEvent::System(SystemWsEvent::AuthRefreshed {
+                        id,
+                        expires_at: ws_auth.expires_at(),
+                    })
+                }
+                Err(error) => WsEvent::System(SystemWsEvent::Error { id, error }),
+            }
+        }
+        SystemWsCommand::AuthBindSessions { id, session_ids } => {
+            match ws_auth.bind_sessions(session_ids) {
+                Ok(()) => WsEvent::System(SystemWsEvent::AuthSessionsBound {
+                    id,
+                    session_ids: ws_auth.bound_sessions().unwrap_or_default(),
+                }),
+                Err(error) => WsEvent::System(SystemWsEvent::Error { id, error }),
+            }
+        }
+    }
+}
+
 fn spawn_ws_command_worker(
     worker_name: &'static str,
     mut rx: mpsc::Receiver<WsCommand>,
@@ -1532,6 +1983,62 @@ async fn handle_ws_command(
     runner_client: Option<&RunnerClient>,
     conn_state: Arc<tokio::sync::Mutex<WsConnectionState>>,
 ) -> Option<WsEvent> {
+    let sent_draft = drafts::sent_draft(&cmd);
+
+    if sent_draft.is_some()
+        && let WsCommand::Agent(agent_cmd) = &cmd
+        && let Some(limiter) = state.rate_limiter.as_ref()
+        && let Err(wait) = limiter.check(Class::AgentMessage, None, Some(user_id))
+    {
+        let (label, _, _) = ws_command_summary(&cmd);
+        return Some(agent_response_with_runner(
+            agent_cmd.runner_id.as_deref().unwrap_or("local"),
+            &agent_cmd.session_id,
+            agent_cmd.id.clone(),
+            label.strip_prefix("agent.").unwrap_or(&label),
+            Err(format!(
+                "Agent message rate limit exceeded, retry in {}s",
+                wait.as_secs_f64().ceil().max(1.0) as u64
+            )),
+        ));
+    }
+
+    // Sessions over budget refuse new prompts until an admin raises it.
+    if let Some(session_id) = &sent_draft
+        && let WsCommand::Agent(agent_cmd) = &cmd
+        && let Some(budget) = state.budget.as_ref()
+        && budget.is_suspended(session_id).await
+    {
+        let (label, _, _) = ws_command_summary(&cmd);
+        return Some(agent_response_with_runner(
+            agent_cmd.runner_id.as_deref().unwrap_or("local"),
+            session_id,
+            agent_cmd.id.clone(),
+            label.strip_prefix("agent.").unwrap_or(&label),
+            Err(
+                "Session budget exceeded; an admin has to raise it before it can continue"
+                    .to_string(),
+            ),
+        ));
+    }
+
+    // Sessions shared with this user run on their owner's runner and are
+    // authorized by the share rather than by workspace path.
+    let (cmd, follow_up) = match cmd {
+        WsCommand::Agent(agent_cmd) => {
+            match session_shares::route(agent_cmd, user_id, state, &conn_state).await {
+                session_shares::Routed::Shared(response) => {
+                    drafts::clear_sent(sent_draft, &response, user_id, state).await;
+                    return response;
+                }
+                session_shares::Routed::Own(agent_cmd, follow_up) => {
+                    (WsCommand::Agent(agent_cmd), follow_up)
+                }
+            }
+        }
+        cmd => (cmd, None),
+    };
+
     // SECURITY: Validate workspace paths belong to this user before processing
     {
         let (_, _, workspace_path) = ws_command_summary(&cmd);
@@ -1542,11 +2049,22 @@ async fn handle_ws_command(
         }
     }
 
-    if let Some(logger) = state.audit_logger.as_ref() {
+    // Capabilities inside shared workspaces (chat/files; terminals are
+    // checked on open).
+    if let Some(err_event) = share_permissions::check_command(&cmd, user_id, state).await {
+        return Some(err_event);
+    }
+
+    // Draft edits are keystrokes; only what is sent gets audited.
+    if let Some(logger) = state.audit_logger.as_ref()
+        && !matches!(cmd, WsCommand::Draft(_))
+    {
         let (label, session_id, workspace_path) = ws_command_summary(&cmd);
+        let impersonation = conn_state.lock().await.impersonation.clone();
         logger
             .log_ws_command(
                 user_id,
+                impersonation.as_ref(),
                 &label,
                 session_id.as_deref(),
                 workspace_path.as_deref(),
@@ -1554,7 +2072,8 @@ async fn handle_ws_command(
             .await;
     }
 
-    match cmd {
+    let follow_up_conn = follow_up.as_ref().map(|_| Arc::clone(&conn_state));
+    let response = match cmd {
         WsCommand::Agent(agent_cmd) => {
             agent::handle_agent_command(agent_cmd, user_id, state, runner_client, conn_state).await
         }
@@ -1574,7 +2093,18 @@ async fn handle_ws_command(
         WsCommand::Bus(bus_cmd) => {
             system::handle_bus_command(bus_cmd, user_id, is_admin, state, conn_state).await
         }
+        WsCommand::Draft(draft_cmd) => {
+            drafts::handle_draft_command(draft_cmd, user_id, state, conn_state).await
+        }
+        // Auth commands are handled by the connection loop and never queued.
+        WsCommand::System(_) => None,
+    };
+
+    if let (Some(follow_up), Some(conn_state)) = (follow_up, follow_up_conn) {
+        follow_up.run(state, &response, &conn_state).await;
     }
+    drafts::clear_sent(sent_draft, &response, user_id, state).await;
+    response
 }
 
 fn sort_dir_entries(
@@ -1644,6 +2174,11 @@ fn ws_command_id(cmd: &WsCommand) -> Option<String> {
             | crate::bus::BusCommand::Unsubscribe { id, .. }
             | crate::bus::BusCommand::Pull { id, .. } => id.clone(),
         },
+        WsCommand::System(system_cmd) => match system_cmd {
+            SystemWsCommand::AuthRefresh { id, .. }
+            | SystemWsCommand::AuthBindSessions { id, .. } => id.clone(),
+        },
+        WsCommand::Draft(draft_cmd) => draft_cmd.id(),
     }
 }
 
@@ -1663,6 +2198,7 @@ fn ws_command_summary(cmd: &WsCommand) -> (String, Option<String>, Option<String
                 oqto_protocol::commands::CommandPayload::SessionRestart => "agent.session_restart",
                 oqto_protocol::commands::CommandPayload::Prompt { .. } => "agent.prompt",
                 oqto_protocol::commands::CommandPayload::Steer { .. } => "agent.steer",
+                oqto_protocol::commands::CommandPayload::Interject { .. } => "agent.interject",
                 oqto_protocol::commands::CommandPayload::FollowUp { .. } => "agent.follow_up",
                 oqto_protocol::commands::CommandPayload::Abort => "agent.abort",
                 oqto_protocol::commands::CommandPayload::InputResponse { .. } => {
@@ -1788,6 +2324,18 @@ fn ws_command_summary(cmd: &WsCommand) -> (String, Option<String>, Option<String
             };
             (label, None, None)
         }
+        WsCommand::System(system_cmd) => {
+            let label = match system_cmd {
+                SystemWsCommand::AuthRefresh { .. } => "system.auth_refresh",
+                SystemWsCommand::AuthBindSessions { .. } => "system.auth_bind_sessions",
+            };
+            (label.to_string(), None, None)
+        }
+        WsCommand::Draft(draft_cmd) => (
+            draft_cmd.label().to_string(),
+            Some(draft_cmd.session_id().to_string()),
+            None,
+        ),
     }
 }
 
@@ -1807,6 +2355,7 @@ fn agent_response_with_runner(
         session_id: session_id.to_string(),
         runner_id: runner_id.to_string(),
         ts: Utc::now().timestamp_millis(),
+        seq: None,
         payload: oqto_protocol::events::EventPayload::Response(
             oqto_protocol::events::CommandResponse {
                 id: id.unwrap_or_default(),
@@ -1848,6 +2397,7 @@ async fn emit_terminal_send_failure(
         session_id: session_id.to_string(),
         runner_id: runner_id.to_string(),
         ts: Utc::now().timestamp_millis(),
+        seq: None,
         payload: oqto_protocol::events::EventPayload::AgentError {
             error,
             recoverable: true,
@@ -1860,6 +2410,7 @@ async fn emit_terminal_send_failure(
         session_id: session_id.to_string(),
         runner_id: runner_id.to_string(),
         ts: Utc::now().timestamp_millis(),
+        seq: None,
         payload: oqto_protocol::events::EventPayload::AgentIdle {
             message_version: None,
         },
@@ -1891,6 +2442,7 @@ async fn arm_response_watchdog(
             session_id: session_id_owned.clone(),
             runner_id: runner_id_owned.clone(),
             ts: Utc::now().timestamp_millis(),
+            seq: None,
             payload: oqto_protocol::events::EventPayload::AgentError {
                 error: "No agent progress received in time. Session recovered to idle; you can retry your message.".to_string(),
                 recoverable: true,
@@ -1903,6 +2455,7 @@ async fn arm_response_watchdog(
             session_id: session_id_owned,
             runner_id: runner_id_owned,
             ts: Utc::now().timestamp_millis(),
+            seq: None,
             payload: oqto_protocol::events::EventPayload::AgentIdle {
                 message_version: None,
             },
@@ -1978,6 +2531,25 @@ async fn broadcast_user_message(
     message: &str,
     client_id: Option<String>,
 ) {
+    for event in &user_message_events(session_id, message, client_id, None) {
+        let legacy = crate::ws::types::WsEvent::AgentEvent {
+            session_id: session_id.to_string(),
+            event: serde_json::to_value(event).unwrap_or_default(),
+        };
+        state
+            .ws_hub
+            .send_to_session_except(session_id, legacy, user_id)
+            .await;
+    }
+}
+
+/// Canonical events that show a user message as if it had been streamed.
+fn user_message_events(
+    session_id: &str,
+    message: &str,
+    client_id: Option<String>,
+    sender: Option<oqto_protocol::Sender>,
+) -> Vec<oqto_protocol::events::Event> {
     let now = chrono::Utc::now().timestamp_millis();
     let msg_id = format!("user-{}", now);
     let user_message = oqto_protocol::messages::Message {
@@ -1985,7 +2557,7 @@ async fn broadcast_user_message(
         idx: 0,
         role: oqto_protocol::messages::Role::User,
         client_id,
-        sender: None,
+        sender,
         parts: vec![hstry_core::parts::Part::Text {
             id: format!("part-{}", now),
             text: message.to_string(),
@@ -2001,11 +2573,12 @@ async fn broadcast_user_message(
         is_error: None,
         metadata: None,
     };
-    let events = vec![
+    vec![
         oqto_protocol::events::Event {
             session_id: session_id.to_string(),
             runner_id: String::new(),
             ts: now,
+            seq: None,
             payload: oqto_protocol::events::EventPayload::StreamMessageStart {
                 message_id: msg_id.clone(),
                 role: "user".to_string(),
@@ -2015,6 +2588,7 @@ async fn broadcast_user_message(
             session_id: session_id.to_string(),
             runner_id: String::new(),
             ts: now,
+            seq: None,
             payload: oqto_protocol::events::EventPayload::StreamTextDelta {
                 message_id: msg_id.clone(),
                 delta: message.to_string(),
@@ -2025,21 +2599,12 @@ async fn broadcast_user_message(
             session_id: session_id.to_string(),
             runner_id: String::new(),
             ts: now,
+            seq: None,
             payload: oqto_protocol::events::EventPayload::StreamMessageEnd {
                 message: user_message,
             },
         },
-    ];
-    for event in &events {
-        let legacy = crate::ws::types::WsEvent::AgentEvent {
-            session_id: session_id.to_string(),
-            event: serde_json::to_value(event).unwrap_or_default(),
-        };
-        state
-            .ws_hub
-            .send_to_session_except(session_id, legacy, user_id)
-            .await;
-    }
+    ]
 }
 
 /// Every command gets a `CommandResponse` event back (or `None` for fire-and-forget
@@ -2047,7 +2612,8 @@ async fn broadcast_user_message(
 async fn handle_get_messages(
     id: Option<String>,
     session_id: &str,
-    _state: &AppState,
+    user_id: &str,
+    state: &AppState,
     runner: &RunnerClient,
     conn_state: Arc<tokio::sync::Mutex<WsConnectionState>>,
     runner_id: &str,
@@ -2083,13 +2649,31 @@ async fn handle_get_messages(
     )
     .await
     {
-        Ok(Ok(resp)) => {
+        Ok(Ok(mut resp)) => {
             debug!(
                 "ws get_messages session={} active={} source=oqto_log count={}",
                 session_id,
                 is_active,
                 resp.messages.len()
             );
+            // Share participants and workspace members must not see what
+            // the owner made private.
+            match crate::api::handlers::private_messages::hidden_from(state, session_id, user_id)
+                .await
+            {
+                Ok(hidden) => {
+                    crate::private_messages::elide(&mut resp.messages, &hidden);
+                }
+                Err(e) => {
+                    warn!("get_messages: private messages lookup failed for {session_id}: {e:#}");
+                    return Some(agent_response(
+                        session_id,
+                        id,
+                        "get_messages",
+                        Err("Failed to check private messages".to_string()),
+                    ));
+                }
+            }
             let mut messages_value = workspace_chat_messages_to_json(resp.messages);
             if let serde_json::Value::Object(ref mut map) = messages_value {
                 let source = match resp.source {
@@ -2137,6 +2721,29 @@ async fn handle_get_messages(
     }
 }
 
+/// Where a forwarder reads agent events from.
+enum AgentEventSource {
+    /// This connection's own runner subscription.
+    Direct(oqto_runner::client::PiSubscription),
+    /// The session's shared stream, which replays missed events.
+    Shared(crate::ws::replay::StreamAttachment),
+}
+
+impl AgentEventSource {
+    /// Next event, and whether it is a replay of one emitted earlier.
+    async fn next(&mut self) -> Option<(PiSubscriptionEvent, bool)> {
+        match self {
+            Self::Direct(subscription) => subscription.next().await.map(|e| (e, false)),
+            Self::Shared(attachment) => {
+                let event = attachment.next().await?;
+                let replayed =
+                    matches!(&event, PiSubscriptionEvent::Event(e) if attachment.is_replayed(e));
+                Some((event, replayed))
+            }
+        }
+    }
+}
+
 /// Forward canonical events from runner subscription to WebSocket.
 ///
 /// The runner's PiTranslator has already converted native Pi events to
@@ -2145,6 +2752,9 @@ async fn handle_get_messages(
 /// If `sub_ready_tx` is provided, signals it once the runner subscription
 /// is confirmed. This allows callers to wait for the subscription before
 /// sending prompts, preventing the race where events are missed.
+///
+/// With shared event streams enabled, events after `last_seen_seq` that
+/// the client missed while disconnected are sent first.
 async fn forward_pi_events(
     runner: &RunnerClient,
     session_id: &str,
@@ -2152,16 +2762,26 @@ async fn forward_pi_events(
     conn_state: Arc<tokio::sync::Mutex<WsConnectionState>>,
     sub_ready_tx: Option<oneshot::Sender<()>>,
     runner_id: String,
+    last_seen_seq: Option<u64>,
 ) -> anyhow::Result<()> {
     info!(
         "forward_pi_events: connecting subscription for session {}",
         session_id
     );
-    let mut subscription = runner.agent_subscribe(session_id).await?;
+    let event_streams = conn_state.lock().await.event_streams.clone();
+    let mut subscription = match event_streams {
+        Some(streams) => AgentEventSource::Shared(
+            streams
+                .attach(runner, &runner_id, session_id, last_seen_seq)
+                .await?,
+        ),
+        None => AgentEventSource::Direct(runner.agent_subscribe(session_id).await?),
+    };
     info!(
         "forward_pi_events: subscription established for session {}",
         session_id
     );
+    watch_runs(&conn_state, runner, session_id).await;
 
     // Signal that the subscription is ready
     if let Some(tx) = sub_ready_tx {
@@ -2170,16 +2790,24 @@ async fn forward_pi_events(
 
     loop {
         match subscription.next().await {
-            Some(PiSubscriptionEvent::Event(canonical_event)) => {
+            Some((PiSubscriptionEvent::Event(canonical_event), true)) => {
+                if event_tx.send(WsEvent::Agent(canonical_event)).is_err() {
+                    break;
+                }
+            }
+            Some((PiSubscriptionEvent::Event(canonical_event), false)) => {
                 // Any real agent event means the command made progress.
                 clear_response_watchdog(&conn_state, session_id).await;
+                observe_tool_usage(&conn_state, &canonical_event).await;
+                observe_usage(&conn_state, &canonical_event).await;
+                observe_agent_crash(&conn_state, runner, &canonical_event).await;
 
                 if event_tx.send(WsEvent::Agent(canonical_event)).is_err() {
                     // WebSocket closed
                     break;
                 }
             }
-            Some(PiSubscriptionEvent::End { reason }) => {
+            Some((PiSubscriptionEvent::End { reason }, _)) => {
                 clear_response_watchdog(&conn_state, session_id).await;
                 debug!(
                     "Pi subscription ended for session {}: {}",
@@ -2187,7 +2815,7 @@ async fn forward_pi_events(
                 );
                 break;
             }
-            Some(PiSubscriptionEvent::Error { code, message }) => {
+            Some((PiSubscriptionEvent::Error { code, message }, _)) => {
                 clear_response_watchdog(&conn_state, session_id).await;
                 error!(
                     "Pi subscription error for session {}: {:?} - {}",
@@ -2198,6 +2826,7 @@ async fn forward_pi_events(
                     session_id: session_id.to_string(),
                     runner_id: runner_id.clone(),
                     ts: chrono::Utc::now().timestamp_millis(),
+                    seq: None,
                     payload: oqto_protocol::events::EventPayload::AgentError {
                         error: format!("Subscription error ({:?}): {}", code, message),
                         recoverable: false,
@@ -2218,6 +2847,115 @@ async fn forward_pi_events(
     Ok(())
 }
 
+/// Feed tool start/end events into the tool usage tracker, if enabled.
+async fn observe_tool_usage(
+    conn_state: &Arc<tokio::sync::Mutex<WsConnectionState>>,
+    event: &oqto_protocol::events::Event,
+) {
+    use oqto_protocol::events::EventPayload;
+
+    if !matches!(
+        event.payload,
+        EventPayload::ToolStart { .. } | EventPayload::ToolEnd { .. }
+    ) {
+        return;
+    }
+
+    let (service, ctx) = {
+        let cs = conn_state.lock().await;
+        let Some(service) = cs.tool_usage.clone() else {
+            return;
+        };
+        let meta = cs.pi_session_meta.get(&event.session_id);
+        let ctx = crate::tool_usage::ToolUsageContext {
+            user_id: cs.user_id.clone(),
+            agent: meta
+                .and_then(|m| m.scope.clone())
+                .unwrap_or_else(|| "pi".to_string()),
+            project: meta
+                .and_then(|m| m.cwd.as_ref())
+                .map(|p| p.to_string_lossy().to_string())
+                .unwrap_or_default(),
+        };
+        (service, ctx)
+    };
+    service.observe(&ctx, event);
+}
+
+/// Feed assistant message usage into spend attribution, if enabled.
+async fn observe_usage(
+    conn_state: &Arc<tokio::sync::Mutex<WsConnectionState>>,
+    event: &oqto_protocol::events::Event,
+) {
+    if !matches!(
+        event.payload,
+        oqto_protocol::events::EventPayload::StreamMessageEnd { .. }
+    ) {
+        return;
+    }
+
+    let (service, user_id) = {
+        let cs = conn_state.lock().await;
+        let Some(service) = cs.usage.clone() else {
+            return;
+        };
+        (service, cs.user_id.clone())
+    };
+    service.observe(&user_id, event);
+}
+
+/// Attach the runner's crash bundle to the session after a fatal agent error.
+async fn observe_agent_crash(
+    conn_state: &Arc<tokio::sync::Mutex<WsConnectionState>>,
+    runner: &RunnerClient,
+    event: &oqto_protocol::events::Event,
+) {
+    if !matches!(
+        event.payload,
+        oqto_protocol::events::EventPayload::AgentError {
+            recoverable: false,
+            ..
+        }
+    ) {
+        return;
+    }
+
+    let (service, user_id) = {
+        let cs = conn_state.lock().await;
+        let Some(service) = cs.crash_bundles.clone() else {
+            return;
+        };
+        (service, cs.user_id.clone())
+    };
+    service.schedule_sync(runner.clone(), user_id, event.session_id.clone());
+}
+
+/// Have the webhooks announce the session's finished agent runs. The watch
+/// is the webhook service's, so it outlives this connection.
+async fn watch_runs(
+    conn_state: &Arc<tokio::sync::Mutex<WsConnectionState>>,
+    runner: &RunnerClient,
+    session_id: &str,
+) {
+    let (service, data) = {
+        let cs = conn_state.lock().await;
+        let Some(service) = cs.webhooks.clone() else {
+            return;
+        };
+        let cwd = cs
+            .pi_session_meta
+            .get(session_id)
+            .and_then(|m| m.cwd.as_ref())
+            .map(|p| p.to_string_lossy().to_string());
+        let data = serde_json::json!({
+            "user_id": cs.user_id,
+            "workdir": cwd,
+        });
+        (service, data)
+    };
+    service.watch_runs(runner, session_id, data).await;
+}
+
 // NOTE: The old pi_event_to_ws_event() function has been removed.
 // Streaming events now flow as canonical events through the PiTranslator
 // in pi_manager.rs and are forwarded directly via WsEvent::Agent.
@@ -2520,29 +3258,59 @@ fn map_tree_node(
 // File watcher
 // ============================================================================
 
+/// Reconnects to a session's fileserver watch before giving up.
+const FILESERVER_WATCH_ATTEMPTS: u32 = 10;
+
+/// Change reported by the `/ws/watch` endpoint of oqto-files.
+#[derive(Debug, Deserialize)]
+struct FileserverWatchEvent {
+    #[serde(rename = "type")]
+    event_type: String,
+    path: String,
+    entry_type: String,
+}
+
 /// Start watching a workspace directory for file changes.
+///
+/// Changes come from the watcher of the workspace's fileserver, which runs
+/// as the workspace owner (inside the container in container mode) and so
+/// sees what the agent edits. Without a fileserver session the backend
+/// watches the directory itself, which only works without user isolation.
 async fn handle_watch_files(
     id: Option<String>,
     workspace_path: &str,
     user_id: &str,
-    _state: &AppState,
+    state: &AppState,
     conn_state: Arc<tokio::sync::Mutex<WsConnectionState>>,
 ) -> Option<WsEvent> {
-    use notify::{
-        RecursiveMode, Watcher,
-        event::{CreateKind, EventKind, RemoveKind},
+    let session = match get_io_session_for_workspace(state, user_id, workspace_path).await {
+        Ok(session) => Some(session),
+        Err(status) => {
+            debug!(
+                "No fileserver session to watch {} for {}: {}",
+                workspace_path, user_id, status
+            );
+            None
+        }
     };
 
     let resolved_path = std::path::PathBuf::from(workspace_path);
-    if !resolved_path.is_dir() {
-        return Some(WsEvent::Files(FilesWsEvent::Error {
-            id,
-            error: format!("Not a directory: {workspace_path}"),
-        }));
+    if session.is_none() {
+        if state.user_isolation_enabled() {
+            return Some(WsEvent::Files(FilesWsEvent::Error {
+                id,
+                error: format!("File watching is unavailable for {workspace_path}"),
+            }));
+        }
+        if !resolved_path.is_dir() {
+            return Some(WsEvent::Files(FilesWsEvent::Error {
+                id,
+                error: format!("Not a directory: {workspace_path}"),
+            }));
+        }
     }
 
     let workspace_key = workspace_path.to_string();
-    let watch_dir = resolved_path.clone();
 
     // Get the event sender from connection state
     let event_tx = {
@@ -2558,136 +3326,314 @@ async fn handle_watch_files(
         }
     }
 
-    let ws_workspace_path = workspace_key.clone();
+    // Dependency changes schedule a vulnerability scan when enabled.
+    let scan_target = state
+        .vuln_scans
+        .as_ref()
+        .filter(|s| s.config().scan_on_change)
+        .map(|_| (state.clone(), user_id.to_string()));
+
+    let handle = match session {
+        Some(session) => tokio::spawn(relay_fileserver_watch(
+            state.clone(),
+            session,
+            workspace_key.clone(),
+            event_tx,
+            scan_target,
+        )),
+        None => tokio::spawn(watch_local_files(
+            resolved_path,
+            workspace_key.clone(),
+            event_tx,
+            scan_target,
+            state.project_cards.clone(),
+        )),
+    };
 
-    // Spawn watcher task
-    let (notify_tx, mut notify_rx) = mpsc::channel::<notify::Result<notify::Event>>(256);
+    // Store the watcher handle
+    {
+        let mut state_guard = conn_state.lock().await;
+        state_guard
+            .file_watchers
+            .insert(workspace_key.clone(), handle);
+    }
 
-    let handle = tokio::spawn(async move {
-        // Create the inotify watcher (must be created on the async runtime thread)
-        let tx_for_watcher = notify_tx.clone();
-        let mut watcher = match notify::recommended_watcher(move |res| {
-            let _ = tx_for_watcher.blocking_send(res);
-        }) {
-            Ok(w) => w,
+    info!(
+        "File watcher started for workspace {} (user {})",
+        workspace_path, user_id
+    );
+
+    Some(WsEvent::Files(FilesWsEvent::WatchFilesResult {
+        id,
+        workspace_path: workspace_key,
+        success: true,
+    }))
+}
+
+/// Send a change to the client unless it is hidden (dotfiles, `.git`
+/// internals) to reduce noise. Returns false once the connection is closed.
+fn send_file_changed(
+    event_tx: &mpsc::UnboundedSender<WsEvent>,
+    workspace_path: &str,
+    event_type: &str,
+    path: String,
+    is_dir: bool,
+) -> bool {
+    if path.is_empty() || path.starts_with('.') || path.contains("/.") {
+        return true;
+    }
+    let ws_event = WsEvent::Files(FilesWsEvent::FileChanged {
+        event_type: event_type.to_string(),
+        path,
+        entry_type: if is_dir { "directory" } else { "file" }.to_string(),
+        workspace_path: workspace_path.to_string(),
+    });
+    event_tx.send(ws_event).is_ok()
+}
+
+/// Relay the watch stream of the session's fileserver until the client
+/// unwatches or disconnects, reconnecting when the fileserver restarts.
+async fn relay_fileserver_watch(
+    state: AppState,
+    session: Session,
+    workspace_path: String,
+    event_tx: mpsc::UnboundedSender<WsEvent>,
+    scan_target: Option<(AppState, String)>,
+) {
+    use tokio_tungstenite::connect_async;
+    use tokio_tungstenite::tungstenite::Message;
+    use tokio_tungstenite::tungstenite::client::IntoClientRequest;
+
+    let url = format!(
+        "ws://127.0.0.1:{}/ws/watch?{}",
+        session.fileserver_port,
+        build_fileserver_query(&workspace_path, Some("path=."))
+    );
+    let mut attempts: u32 = 0;
+
+    while !event_tx.is_closed() {
+        // Tokens are short-lived, so every connection gets a fresh one.
+        let token = match state
+            .sessions
+            .fileserver_token(&session.id, &workspace_path, CapabilityScope::Read)
+            .await
+        {
+            Ok(token) => token,
             Err(e) => {
                 warn!(
-                    "Failed to create file watcher for {}: {:?}",
-                    ws_workspace_path, e
+                    "Failed to mint fileserver token for session {}: {:?}",
+                    session.id, e
                 );
                 return;
             }
         };
+        let mut request = match url.as_str().into_client_request() {
+            Ok(request) => request,
+            Err(e) => {
+                warn!("Invalid fileserver watch URL {}: {:?}", url, e);
+                return;
+            }
+        };
+        if let Some(token) = token
+            && let Ok(value) = axum::http::HeaderValue::from_str(&token)
+        {
+            request.headers_mut().insert(TOKEN_HEADER, value);
+        }
 
-        if let Err(e) = watcher.watch(&watch_dir, RecursiveMode::Recursive) {
-            warn!("Failed to watch {}: {:?}", watch_dir.display(), e);
+        match connect_async(request).await {
+            Ok((mut socket, _response)) => {
+                attempts = 0;
+                debug!(
+                    "Relaying fileserver watch of session {} for {}",
+                    session.id, workspace_path
+                );
+                while let Some(message) = socket.next().await {
+                    let text = match message {
+                        Ok(Message::Text(text)) => text,
+                        Ok(Message::Close(_)) | Err(_) => break,
+                        Ok(_) => continue,
+                    };
+                    let Ok(change) = serde_json::from_str::<FileserverWatchEvent>(&text) else {
+                        continue;
+                    };
+                    if let Some(cards) = &state.project_cards {
+                        cards.invalidate(std::path::Path::new(&workspace_path));
+                    }
+                    let is_dir = change.entry_type == "directory";
+                    let dependency_changed =
+                        !is_dir && crate::vuln_scan::is_dependency_file(&change.path);
+                    if !send_file_changed(
+                        &event_tx,
+                        &workspace_path,
+                        &change.event_type,
+                        change.path,
+                        is_dir,
+                    ) {
+                        return;
+                    }
+                    if dependency_changed && let Some((state, user_id)) = &scan_target {
+                        crate::api::handlers::vulnerabilities::schedule_after_dependency_change(
+                            state,
+                            user_id,
+                            &workspace_path,
+                        )
+                        .await;
+                    }
+                }
+            }
+            Err(e) => debug!(
+                "Fileserver watch of session {} not available: {}",
+                session.id, e
+            ),
+        }
+
+        attempts += 1;
+        if attempts > FILESERVER_WATCH_ATTEMPTS {
+            warn!(
+                "Giving up on fileserver watch of session {} for {}",
+                session.id, workspace_path
+            );
+            let _ = event_tx.send(WsEvent::Files(FilesWsEvent::Error {
+                id: None,
+                error: format!("File watching stopped for {workspace_path}"),
+            }));
             return;
         }
+        let backoff_ms = (attempts as u64) * 500;
+        tokio::time::sleep(Duration::from_millis(backoff_ms)).await;
+    }
+}
 
-        debug!("File watcher started for {}", ws_workspace_path);
+/// Watch a workspace directory the backend can read itself.
+async fn watch_local_files(
+    watch_dir: std::path::PathBuf,
+    ws_workspace_path: String,
+    event_tx: mpsc::UnboundedSender<WsEvent>,
+    scan_target: Option<(AppState, String)>,
+    project_cards: Option<Arc<crate::project_cards::ProjectCardService>>,
+) {
+    use notify::{
+        RecursiveMode, Watcher,
+        event::{CreateKind, EventKind, ModifyKind, RemoveKind},
+    };
 
-        // Debounce: collect events and flush after 300ms of quiet
-        let debounce = Duration::from_millis(300);
-        let mut pending: HashMap<std::path::PathBuf, EventKind> = HashMap::new();
-        let mut deadline: Option<tokio::time::Instant> = None;
+    let (notify_tx, mut notify_rx) = mpsc::channel::<notify::Result<notify::Event>>(256);
 
-        loop {
-            tokio::select! {
-                event = notify_rx.recv() => {
-                    match event {
-                        Some(Ok(ev)) => {
-                            for path in ev.paths {
-                                pending.insert(path, ev.kind);
-                            }
-                            deadline = Some(tokio::time::Instant::now() + debounce);
-                        }
-                        Some(Err(e)) => {
-                            warn!("File watcher error: {:?}", e);
+    // Create the inotify watcher (must be created on the async runtime thread)
+    let tx_for_watcher = notify_tx.clone();
+    let mut watcher = match notify::recommended_watcher(move |res| {
+        let _ = tx_for_watcher.blocking_send(res);
+    }) {
+        Ok(w) => w,
+        Err(e) => {
+            warn!(
+                "Failed to create file watcher for {}: {:?}",
+                ws_workspace_path, e
+            );
+            return;
+        }
+    };
+
+    if let Err(e) = watcher.watch(&watch_dir, RecursiveMode::Recursive) {
+        warn!("Failed to watch {}: {:?}", watch_dir.display(), e);
+        return;
+    }
+
+    debug!("File watcher started for {}", ws_workspace_path);
+
+    // Debounce: collect events and flush after 300ms of quiet
+    let debounce = Duration::from_millis(300);
+    let mut pending: HashMap<std::path::PathBuf, EventKind> = HashMap::new();
+    let mut deadline: Option<tokio::time::Instant> = None;
+
+    loop {
+        tokio::select! {
+            event = notify_rx.recv() => {
+                match event {
+                    Some(Ok(ev)) => {
+                        for path in ev.paths {
+                            pending.insert(path, ev.kind);
                         }
-                        None => break,
+                        deadline = Some(tokio::time::Instant::now() + debounce);
                     }
+                    Some(Err(e)) => {
+                        warn!("File watcher error: {:?}", e);
+                    }
+                    None => break,
+                }
+            }
+            _ = tokio::time::sleep_until(deadline.unwrap_or_else(|| tokio::time::Instant::now() + Duration::from_secs(3600))), if deadline.is_some() => {
+                let batch: HashMap<_, _> = std::mem::take(&mut pending);
+                deadline = None;
+                let mut dependencies_changed = false;
+                if let Some(cards) = &project_cards {
+                    cards.invalidate(&watch_dir);
                 }
-                _ = tokio::time::sleep_until(deadline.unwrap_or_else(|| tokio::time::Instant::now() + Duration::from_secs(3600))), if deadline.is_some() => {
-                    let batch: HashMap<_, _> = std::mem::take(&mut pending);
-                    deadline = None;
 
-                    for (path, kind) in batch {
-                        if !path.starts_with(&watch_dir) {
-                            continue;
-                        }
+                for (path, kind) in batch {
+                    if !path.starts_with(&watch_dir) {
+                        continue;
+                    }
 
-                        let is_dir = match tokio::fs::metadata(&path).await {
-                            Ok(m) => m.is_dir(),
-                            Err(_) => matches!(
-                                kind,
-                                EventKind::Create(CreateKind::Folder) | EventKind::Remove(RemoveKind::Folder)
-                            ),
-                        };
-
-                        let event_type = match kind {
-                            EventKind::Create(_) => {
-                                if is_dir { "dir_created" } else { "file_created" }
-                            }
-                            EventKind::Modify(_) => {
-                                if is_dir { continue; } else { "file_modified" }
-                            }
-                            EventKind::Remove(_) => {
-                                if is_dir { "dir_deleted" } else { "file_deleted" }
-                            }
-                            _ => continue,
-                        };
-
-                        // Compute relative path
-                        let rel = path.strip_prefix(&watch_dir)
-                            .map(|p| p.to_string_lossy().to_string())
-                            .unwrap_or_default();
-                        if rel.is_empty() {
-                            continue;
-                        }
+                    let metadata = tokio::fs::metadata(&path).await;
+                    let exists = metadata.is_ok();
+                    let is_dir = match metadata {
+                        Ok(m) => m.is_dir(),
+                        Err(_) => matches!(
+                            kind,
+                            EventKind::Create(CreateKind::Folder) | EventKind::Remove(RemoveKind::Folder)
+                        ),
+                    };
 
-                        // Skip hidden files / .git internals to reduce noise
-                        if rel.starts_with('.') || rel.contains("/.") {
-                            continue;
+                    let event_type = match kind {
+                        // Renames: the old path is gone, the new one appeared.
+                        EventKind::Modify(ModifyKind::Name(_)) => match (exists, is_dir) {
+                            (true, true) => "dir_created",
+                            (true, false) => "file_created",
+                            (false, true) => "dir_deleted",
+                            (false, false) => "file_deleted",
+                        },
+                        EventKind::Create(_) => {
+                            if is_dir { "dir_created" } else { "file_created" }
                         }
+                        EventKind::Modify(_) => {
+                            if is_dir { continue; } else { "file_modified" }
+                        }
+                        EventKind::Remove(_) => {
+                            if is_dir { "dir_deleted" } else { "file_deleted" }
+                        }
+                        _ => continue,
+                    };
 
-                        let ws_event = WsEvent::Files(FilesWsEvent::FileChanged {
-                            event_type: event_type.to_string(),
-                            path: rel,
-                            entry_type: if is_dir { "directory".to_string() } else { "file".to_string() },
-                            workspace_path: ws_workspace_path.clone(),
-                        });
+                    // Compute relative path
+                    let rel = path.strip_prefix(&watch_dir)
+                        .map(|p| p.to_string_lossy().to_string())
+                        .unwrap_or_default();
 
-                        if event_tx.send(ws_event).is_err() {
-                            // Connection closed
-                            break;
-                        }
+                    if !is_dir && crate::vuln_scan::is_dependency_file(&rel) {
+                        dependencies_changed = true;
                     }
+
+                    if !send_file_changed(&event_tx, &ws_workspace_path, event_type, rel, is_dir) {
+                        // Connection closed
+                        return;
+                    }
+                }
+
+                if dependencies_changed && let Some((state, user_id)) = &scan_target {
+                    crate::api::handlers::vulnerabilities::schedule_after_dependency_change(
+                        state,
+                        user_id,
+                        &ws_workspace_path,
+                    )
+                    .await;
                 }
             }
         }
-
-        debug!("File watcher stopped for {}", ws_workspace_path);
-        // `watcher` is dropped here, which stops inotify
-    });
-
-    // Store the watcher handle
-    {
-        let mut state_guard = conn_state.lock().await;
-        state_guard
-            .file_watchers
-            .insert(workspace_key.clone(), handle);
     }
 
-    info!(
-        "File watcher started for workspace {} (user {})",
-        workspace_path, user_id
-    );
-
-    Some(WsEvent::Files(FilesWsEvent::WatchFilesResult {
-        id,
-        workspace_path: workspace_key,
-        success: true,
-    }))
+    debug!("File watcher stopped for {}", ws_workspace_path);
+    // `watcher` is dropped here, which stops inotify
 }
 
 /// Stop watching a workspace directory.
@@ -2837,6 +3783,7 @@ mod tests {
             session_id: "ses_123".into(),
             runner_id: "local".into(),
             ts: 1738764000000,
+            seq: None,
             payload: EventPayload::Response(CommandResponse {
                 id: "req-1".into(),
                 cmd: "session.create".into(),
@@ -2854,10 +3801,15 @@ mod tests {
 
     #[test]
     fn test_serialize_system_connected() {
-        let event = WsEvent::System(SystemWsEvent::Connected);
+        let registry = crate::capabilities::CapabilityRegistry::default();
+        registry.enable(crate::capabilities::Subsystem::Eavs);
+        let event = WsEvent::System(SystemWsEvent::Connected {
+            capabilities: registry.snapshot(),
+        });
         let json = serde_json::to_string(&event).unwrap();
         assert!(json.contains(r#""channel":"system""#));
         assert!(json.contains(r#""type":"connected""#));
+        assert!(json.contains(r#""subsystem":"eavs","state":"enabled""#));
     }
 
     #[test]
@@ -2880,6 +3832,7 @@ mod tests {
             session_id: "ses_abc".into(),
             runner_id: "local".into(),
             ts: 1738764000000,
+            seq: None,
             payload: EventPayload::StreamTextDelta {
                 message_id: "msg-1".into(),
                 delta: "Hello".into(),
@@ -2901,6 +3854,7 @@ mod tests {
             session_id: "ses_abc".into(),
             runner_id: "local".into(),
             ts: 1738764000000,
+            seq: None,
             payload: EventPayload::AgentIdle {
                 message_version: None,
             },
@@ -3041,6 +3995,14 @@ mod tests {
             file_watchers: HashMap::new(),
             session_runner_overrides: HashMap::new(),
             bus_subscriber_id: 0,
+            user_id: "test-user".to_string(),
+            tool_usage: None,
+            usage: None,
+            crash_bundles: None,
+            webhooks: None,
+            event_streams: None,
+            client: ClientKind::Interactive,
+            impersonation: None,
         }));
 
         emit_terminal_send_failure(
@@ -82,7 +82,7 @@ pub(super) async fn handle_agent_command(
         if let Some(ovr) = override_runner {
             tracing::debug!(session_id = %session_id, socket = ?ovr.socket_path(), "using stored runner override");
             ovr
-        } else if let CommandPayload::SessionCreate { ref config } = cmd.payload {
+        } else if let CommandPayload::SessionCreate { ref config, .. } = cmd.payload {
             // For session.create, check if cwd is inside a shared workspace
             tracing::info!(session_id = %session_id, cwd = ?config.cwd, "session.create: checking cwd for shared workspace routing");
             let sw_runner = if let Some(ref cwd) = config.cwd {
@@ -301,6 +301,7 @@ pub(super) async fn handle_agent_command(
                                         PiSessionMeta {
                                             scope: None,
                                             cwd: Some(std::path::PathBuf::from(workspace_path)),
+                                            sandbox_profile: None,
                                         },
                                     );
                                 }
@@ -396,10 +397,62 @@ pub(super) async fn handle_agent_command(
         }
     };
 
+    // With federated runner hosts, personal sessions run on the host they
+    // were placed on (failover may move them); session.create places new ones.
+    let resolved_runner = match (&state.runner_pool, runner_client) {
+        (Some(pool), Some(personal))
+            if session_id != "_system"
+                && (resolved_runner.socket_path() == personal.socket_path()
+                    || pool.owns(&resolved_runner)) =>
+        {
+            let place = matches!(cmd.payload, CommandPayload::SessionCreate { .. });
+            match pool
+                .runner_for_session(user_id, &session_id, place, personal)
+                .await
+            {
+                Ok(client) => {
+                    let mut state_guard = conn_state.lock().await;
+                    if client.socket_path() == personal.socket_path() {
+                        state_guard.session_runner_overrides.remove(&session_id);
+                    } else {
+                        state_guard
+                            .session_runner_overrides
+                            .insert(session_id.clone(), client.clone());
+                    }
+                    client
+                }
+                Err(e) => {
+                    return Some(agent_response(
+                        &session_id,
+                        id,
+                        "error",
+                        Err(format!("No runner host available: {e:#}")),
+                    ));
+                }
+            }
+        }
+        _ => resolved_runner,
+    };
+
     let runner = &resolved_runner;
 
+    if let Some(lanes) = &state.priority_lanes
+        && matches!(
+            cmd.payload,
+            CommandPayload::Prompt { .. }
+                | CommandPayload::Steer { .. }
+                | CommandPayload::Interject { .. }
+                | CommandPayload::FollowUp { .. }
+        )
+    {
+        lanes.mark_interactive();
+    }
+
     match cmd.payload {
-        CommandPayload::SessionCreate { config } => {
+        CommandPayload::SessionCreate {
+            config,
+            last_seen_seq,
+        } => {
             info!(
                 "agent session.create: user={}, session_id={}",
                 user_id, session_id
@@ -425,6 +478,14 @@ pub(super) async fn handle_agent_command(
                 }
             }
 
+            // A resume re-creates the runner session and replays what the
+            // client missed; after a restart everyone does it at once.
+            if last_seen_seq.is_some()
+                && let Err(e) = admit_replay(state, &conn_state, id.as_deref(), &session_id).await
+            {
+                return Some(agent_response(&session_id, id, "session.create", Err(e)));
+            }
+
             let mut cwd = config
                 .cwd
                 .as_ref()
@@ -452,6 +513,7 @@ pub(super) async fn handle_agent_command(
                     PiSessionMeta {
                         scope: Some(config.harness.clone()),
                         cwd: Some(cwd.clone()),
+                        sandbox_profile: config.sandbox_profile.clone(),
                     },
                 );
             }
@@ -486,6 +548,8 @@ pub(super) async fn handle_agent_command(
                 session_file: None,
                 continue_session,
                 env: std::collections::HashMap::new(),
+                harness: Some(config.harness).filter(|h| !h.is_empty()),
+                sandbox_profile: config.sandbox_profile,
             };
 
             let req = PiCreateSessionRequest {
@@ -535,6 +599,7 @@ pub(super) async fn handle_agent_command(
                                 conn_state_for_fwd,
                                 Some(sub_ready_tx),
                                 runner_id,
+                                last_seen_seq,
                             )
                             .await
                             {
@@ -812,12 +877,14 @@ pub(super) async fn handle_agent_command(
             );
 
             let state_before_restart = runner.agent_get_state(&session_id).await.ok();
-            let cwd = {
+            let (cwd, harness, sandbox_profile) = {
                 let state_guard = conn_state.lock().await;
-                state_guard
-                    .pi_session_meta
-                    .get(&session_id)
-                    .and_then(|m| m.cwd.clone())
+                let meta = state_guard.pi_session_meta.get(&session_id);
+                (
+                    meta.and_then(|m| m.cwd.clone()),
+                    meta.and_then(|m| m.scope.clone()),
+                    meta.and_then(|m| m.sandbox_profile.clone()),
+                )
             };
             let cwd = if let Some(cwd) = cwd {
                 cwd
@@ -865,8 +932,9 @@ pub(super) async fn handle_agent_command(
                 state_guard.pi_session_meta.insert(
                     session_id.clone(),
                     PiSessionMeta {
-                        scope: Some("pi".to_string()),
+                        scope: Some(harness.clone().unwrap_or_else(|| "pi".to_string())),
                         cwd: Some(cwd.clone()),
+                        sandbox_profile: sandbox_profile.clone(),
                     },
                 );
             }
@@ -882,6 +950,8 @@ pub(super) async fn handle_agent_command(
                     session_file: None,
                     continue_session,
                     env: std::collections::HashMap::new(),
+                    harness,
+                    sandbox_profile,
                 },
             };
 
@@ -904,6 +974,7 @@ pub(super) async fn handle_agent_command(
                             conn_state_for_fwd,
                             Some(sub_ready_tx),
                             runner_id,
+                            None,
                         )
                         .await
                         {
@@ -1010,6 +1081,9 @@ pub(super) async fn handle_agent_command(
                             client_id_for_broadcast,
                         )
                         .await;
+                        if let Some(tags) = &state.session_tags {
+                            tags.observe_prompt(user_id, &session_id, &message);
+                        }
                         Some(agent_response(&session_id, id, "prompt", Ok(None)))
                     }
                     Err(e) => {
@@ -1104,6 +1178,87 @@ pub(super) async fn handle_agent_command(
             }
         }
 
+        CommandPayload::Interject { message, client_id } => {
+            if has_accepted_client_id(&session_id, client_id.as_deref()).await {
+                return Some(agent_response(&session_id, id, "interject", Ok(None)));
+            }
+            if message.trim().is_empty() {
+                warn!(
+                    "agent interject rejected empty message: user={}, session_id={}",
+                    user_id, session_id
+                );
+                Some(agent_response(
+                    &session_id,
+                    id,
+                    "interject",
+                    Err("Empty interjection is not allowed".to_string()),
+                ))
+            } else {
+                let effective_message = tag_shared_workspace_message(
+                    state,
+                    &conn_state,
+                    &session_id,
+                    user_id,
+                    &message,
+                )
+                .await;
+                info!(
+                    "agent interject: user={}, session_id={}, len={}, client_id={:?}",
+                    user_id,
+                    session_id,
+                    effective_message.len(),
+                    client_id
+                );
+                let client_id_for_broadcast = client_id.clone();
+                let client_id_for_dedupe = client_id.clone();
+                match runner
+                    .agent_interject(&session_id, &effective_message, client_id)
+                    .await
+                {
+                    Ok(interjected) => {
+                        mark_client_id_accepted(&session_id, client_id_for_dedupe.as_deref()).await;
+                        let event_tx = {
+                            let state_guard = conn_state.lock().await;
+                            state_guard.event_tx.clone()
+                        };
+                        arm_response_watchdog(&conn_state, &session_id, &runner_id, event_tx).await;
+                        broadcast_user_message(
+                            state,
+                            &session_id,
+                            user_id,
+                            &effective_message,
+                            client_id_for_broadcast,
+                        )
+                        .await;
+                        Some(agent_response(
+                            &session_id,
+                            id,
+                            "interject",
+                            Ok(Some(serde_json::json!({
+                                "interjection_id": interjected.interjection_id,
+                                "delivery": interjected.delivery,
+                            }))),
+                        ))
+                    }
+                    Err(e) => {
+                        let error_msg = format!("Failed to interject: {}", e);
+                        warn!(
+                            "agent interject failed: user={}, session_id={}, error={}",
+                            user_id, session_id, error_msg
+                        );
+                        emit_terminal_send_failure(
+                            &conn_state,
+                            &session_id,
+                            &runner_id,
+                            error_msg.clone(),
+                        )
+                        .await;
+                        Some(agent_response(&session_id, id, "interject", Err(error_msg)))
+                    }
+                }
+            }
+        }
+
         CommandPayload::FollowUp { message, client_id } => {
             if has_accepted_client_id(&session_id, client_id.as_deref()).await {
                 return Some(agent_response(&session_id, id, "follow_up", Ok(None)));
@@ -1252,7 +1407,16 @@ pub(super) async fn handle_agent_command(
                 "agent get_messages: user={}, session_id={}",
                 user_id, session_id
             );
-            handle_get_messages(id, &session_id, state, runner, conn_state, &runner_id).await
+            handle_get_messages(
+                id,
+                &session_id,
+                user_id,
+                state,
+                runner,
+                conn_state,
+                &runner_id,
+            )
+            .await
         }
 
         CommandPayload::GetStats => {
@@ -1351,6 +1515,24 @@ pub(super) async fn handle_agent_command(
                 "agent get_fork_points: user={}, session_id={}",
                 user_id, session_id
             );
+            // Fork points are Pi entries, which cannot be matched to
+            // private messages, so their previews go whenever anything in
+            // the session is hidden from the caller.
+            let elide = match crate::api::handlers::private_messages::hidden_from(
+                state,
+                &session_id,
+                user_id,
+            )
+            .await
+            {
+                Ok(hidden) => !hidden.is_empty(),
+                Err(e) => {
+                    warn!(
+                        "get_fork_points: private messages lookup failed for {session_id}: {e:#}"
+                    );
+                    true
+                }
+            };
             match runner.agent_get_fork_messages(&session_id).await {
                 Ok(resp) => {
                     let messages: Vec<Value> = resp
@@ -1360,7 +1542,11 @@ pub(super) async fn handle_agent_command(
                             serde_json::json!({
                                 "entry_id": m.entry_id,
                                 "role": "user",
-                                "preview": m.text,
+                                "preview": if elide {
+                                    crate::private_messages::ELIDED_TEXT.to_string()
+                                } else {
+                                    m.text
+                                },
                             })
                         })
                         .collect();
@@ -1395,6 +1581,7 @@ pub(super) async fn handle_agent_command(
                         session_id: session_id.clone(),
                         runner_id: runner_id.clone(),
                         ts: Utc::now().timestamp_millis(),
+                        seq: None,
                         payload: oqto_protocol::events::EventPayload::ConfigModelChanged {
                             provider: resp.model.provider.clone(),
                             model_id: resp.model.id.clone(),
@@ -1435,6 +1622,7 @@ pub(super) async fn handle_agent_command(
                         session_id: session_id.clone(),
                         runner_id: runner_id.clone(),
                         ts: Utc::now().timestamp_millis(),
+                        seq: None,
                         payload: oqto_protocol::events::EventPayload::ConfigModelChanged {
                             provider: resp.model.provider.clone(),
                             model_id: resp.model.id.clone(),
@@ -1631,6 +1819,7 @@ pub(super) async fn handle_agent_command(
                             "model": s.model,
                             "last_activity": s.last_activity,
                             "subscriber_count": s.subscriber_count,
+                            "sandbox_profile": s.sandbox_profile,
                         });
                         if let Some(ref hid) = s.hstry_id {
                             obj["hstry_id"] = serde_json::Value::String(hid.clone());
@@ -1702,6 +1891,7 @@ pub(super) async fn handle_agent_command(
                                                 PiSessionMeta {
                                                     scope: Some("pi".to_string()),
                                                     cwd: Some(std::path::PathBuf::from(&s.cwd)),
+                                                    sandbox_profile: None,
                                                 },
                                             );
                                         }
@@ -1717,6 +1907,7 @@ pub(super) async fn handle_agent_command(
                                         "model": s.model,
                                         "last_activity": s.last_activity,
                                         "subscriber_count": s.subscriber_count,
+                                        "sandbox_profile": s.sandbox_profile,
                                         "shared_workspace_id": ws.id,
                                     });
                                     if let Some(ref hid) = s.hstry_id {
@@ -0,0 +1,356 @@
+//! Socket-level auth enforcement: token TTL, session binding, revocation.
+
+use super::*;
+use crate::auth::{Actor, RevocationScope, TokenRevocation};
+
+/// Warn the client this long before its token expires so it can refresh.
+pub(super) const AUTH_EXPIRY_WARNING_SECS: i64 = 60;
+/// Close code sent when the socket's token expires without a refresh.
+pub(super) const WS_CLOSE_TOKEN_EXPIRED: u16 = 4001;
+/// Close code sent when the socket's credentials are revoked.
+pub(super) const WS_CLOSE_TOKEN_REVOKED: u16 = 4003;
+
+/// What happens when the next auth timer fires.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) enum AuthDeadline {
+    /// Emit `auth.expiring` so the client can refresh.
+    Warn,
+    /// Close the socket.
+    Expire,
+}
+
+/// Auth state for one multiplexed WebSocket connection.
+#[derive(Debug)]
+pub(super) struct WsAuthState {
+    expires_at: i64,
+    via_api_key: bool,
+    /// Admin acting as the user, when the socket was opened with an
+    /// impersonation token.
+    impersonation: Option<Actor>,
+    /// When set, only commands for these session IDs are accepted.
+    bound_sessions: Option<HashSet<String>>,
+    expiry_warned: bool,
+}
+
+impl WsAuthState {
+    pub(super) fn new(claims: &Claims, bound_sessions: Option<HashSet<String>>) -> Self {
+        Self {
+            expires_at: claims.exp,
+            via_api_key: is_api_key_claims(claims),
+            impersonation: claims.act.clone(),
+            bound_sessions,
+            expiry_warned: false,
+        }
+    }
+
+    pub(super) fn expires_at(&self) -> i64 {
+        self.expires_at
+    }
+
+    pub(super) fn impersonation(&self) -> Option<&Actor> {
+        self.impersonation.as_ref()
+    }
+
+    /// Bound session IDs in stable order (for client-facing events).
+    pub(super) fn bound_sessions(&self) -> Option<Vec<String>> {
+        self.bound_sessions.as_ref().map(|ids| {
+            let mut ids: Vec<String> = ids.iter().cloned().collect();
+            ids.sort();
+            ids
+        })
+    }
+
+    /// Time until the next auth timer fires, and what it means.
+    pub(super) fn next_deadline(&self, now: i64) -> (Duration, AuthDeadline) {
+        let remaining = (self.expires_at - now).max(0);
+        if self.expiry_warned {
+            (Duration::from_secs(remaining as u64), AuthDeadline::Expire)
+        } else {
+            let until_warn = (remaining - AUTH_EXPIRY_WARNING_SECS).max(0);
+            (Duration::from_secs(until_warn as u64), AuthDeadline::Warn)
+        }
+    }
+
+    pub(super) fn mark_expiry_warned(&mut self) {
+        self.expiry_warned = true;
+    }
+
+    /// Replace the socket's credentials with freshly validated claims.
+    pub(super) fn refresh(&mut self, claims: &Claims, user_id: &str) -> Result<(), String> {
+        if claims.sub != user_id {
+            return Err("Token belongs to a different user".to_string());
+        }
+        if claims.exp <= Utc::now().timestamp() {
+            return Err("Token already expired".to_string());
+        }
+        // An impersonated socket stays watermarked, and a user's own socket
+        // cannot turn into an impersonated one.
+        if claims.act != self.impersonation {
+            return Err("Token is for a different impersonation".to_string());
+        }
+        // Opening the socket needed `write`; so does keeping it open.
+        if !claims.has_scope(crate::api_keys::SCOPE_WRITE) {
+            return Err("Token lacks the 'write' scope".to_string());
+        }
+        self.expires_at = claims.exp;
+        self.via_api_key = is_api_key_claims(claims);
+        self.expiry_warned = false;
+        Ok(())
+    }
+
+    /// Bind the socket to a set of sessions.
+    ///
+    /// Binding can only narrow: once bound, a socket may not add sessions.
+    pub(super) fn bind_sessions(&mut self, session_ids: Vec<String>) -> Result<(), String> {
+        let requested: HashSet<String> = session_ids
+            .into_iter()
+            .map(|id| id.trim().to_string())
+            .filter(|id| !id.is_empty())
+            .collect();
+        if requested.is_empty() {
+            return Err("At least one session ID is required".to_string());
+        }
+        if let Some(existing) = self.bound_sessions.as_ref()
+            && !requested.is_subset(existing)
+        {
+            return Err("Socket is already bound; bindings can only be narrowed".to_string());
+        }
+        self.bound_sessions = Some(requested);
+        Ok(())
+    }
+
+    /// Whether a command targeting `session_id` may run on this socket.
+    ///
+    /// Commands without a session ID (list, files, bus, ...) are not
+    /// session-scoped and are always allowed.
+    pub(super) fn permits_session(&self, session_id: Option<&str>) -> bool {
+        match (self.bound_sessions.as_ref(), session_id) {
+            (Some(bound), Some(id)) if !id.is_empty() => bound.contains(id),
+            _ => true,
+        }
+    }
+
+    /// Whether a revocation notice applies to this socket.
+    pub(super) fn is_revoked_by(&self, revocation: &TokenRevocation, user_id: &str) -> bool {
+        if revocation.user_id != user_id {
+            return false;
+        }
+        match revocation.scope {
+            RevocationScope::AllTokens => true,
+            RevocationScope::ApiKeys => self.via_api_key,
+            RevocationScope::Impersonation => self.impersonation.is_some(),
+        }
+    }
+}
+
+fn is_api_key_claims(claims: &Claims) -> bool {
+    claims.iss.as_deref() == Some("api_key")
+}
+
+/// Parse the `sessions` query parameter (comma-separated session IDs).
+pub(super) fn parse_bound_sessions(raw: Option<&str>) -> Option<HashSet<String>> {
+    let ids: HashSet<String> = raw?
+        .split(',')
+        .map(str::trim)
+        .filter(|id| !id.is_empty())
+        .map(str::to_string)
+        .collect();
+    if ids.is_empty() { None } else { Some(ids) }
+}
+
+/// Validate a refresh token presented over the socket.
+pub(super) async fn validate_refresh_token(
+    state: &AppState,
+    token: &str,
+) -> Result<Claims, crate::auth::AuthError> {
+    if crate::api_keys::is_api_key(token) {
+        crate::auth::api_key_claims(&state.api_keys, token).await
+    } else {
+        let claims = state.auth.validate_token(token)?;
+        crate::auth::check_impersonation(
+            state.impersonations.as_ref().map(|service| service.repo()),
+            &claims,
+        )
+        .await?;
+        Ok(claims)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn claims(sub: &str, exp: i64, iss: Option<&str>) -> Claims {
+        Claims {
+            sub: sub.to_string(),
+            iss: iss.map(str::to_string),
+            aud: None,
+            exp,
+            iat: None,
+            nbf: None,
+            jti: None,
+            email: None,
+            name: None,
+            preferred_username: None,
+            roles: vec![],
+            role: None,
+            act: None,
+            scope: None,
+        }
+    }
+
+    fn actor() -> Actor {
+        Actor {
+            sub: "admin".to_string(),
+            impersonation_id: "imp_1".to_string(),
+        }
+    }
+
+    fn revocation(user_id: &str, scope: RevocationScope) -> TokenRevocation {
+        TokenRevocation {
+            user_id: user_id.to_string(),
+            scope,
+            reason: "test".to_string(),
+            revoked_at: 0,
+        }
+    }
+
+    #[test]
+    fn test_parse_bound_sessions() {
+        assert_eq!(parse_bound_sessions(None), None);
+        assert_eq!(parse_bound_sessions(Some(" , ")), None);
+        let ids = parse_bound_sessions(Some("a, b,,a")).unwrap();
+        assert_eq!(ids.len(), 2);
+        assert!(ids.contains("a") && ids.contains("b"));
+    }
+
+    #[test]
+    fn test_deadline_warns_then_expires() {
+        let mut auth = WsAuthState::new(&claims("u", 1_000, None), None);
+        assert_eq!(
+            auth.next_deadline(800),
+            (Duration::from_secs(140), AuthDeadline::Warn)
+        );
+        auth.mark_expiry_warned();
+        assert_eq!(
+            auth.next_deadline(950),
+            (Duration::from_secs(50), AuthDeadline::Expire)
+        );
+        assert_eq!(
+            auth.next_deadline(2_000),
+            (Duration::ZERO, AuthDeadline::Expire)
+        );
+    }
+
+    #[test]
+    fn test_refresh_requires_same_user() {
+        let future = Utc::now().timestamp() + 3600;
+        let mut auth = WsAuthState::new(&claims("u", 1_000, None), None);
+        auth.mark_expiry_warned();
+
+        assert!(auth.refresh(&claims("other", future, None), "u").is_err());
+        assert!(auth.refresh(&claims("u", 10, None), "u").is_err());
+
+        let read_only = Claims {
+            scope: Some("read".to_string()),
+            ..claims("u", future, Some("api_key"))
+        };
+        assert!(auth.refresh(&read_only, "u").is_err());
+
+        auth.refresh(&claims("u", future, None), "u").unwrap();
+        assert_eq!(auth.expires_at(), future);
+        assert!(!auth.expiry_warned);
+    }
+
+    #[test]
+    fn test_session_binding_only_narrows() {
+        let mut auth = WsAuthState::new(&claims("u", 1_000, None), None);
+        assert!(auth.permits_session(Some("anything")));
+
+        auth.bind_sessions(vec!["a".into(), "b".into()]).unwrap();
+        assert!(auth.permits_session(Some("a")));
+        assert!(!auth.permits_session(Some("c")));
+        assert!(auth.permits_session(None));
+        assert!(auth.permits_session(Some("")));
+
+        assert!(auth.bind_sessions(vec!["c".into()]).is_err());
+        auth.bind_sessions(vec!["a".into()]).unwrap();
+        assert!(!auth.permits_session(Some("b")));
+        assert!(auth.bind_sessions(vec![" ".into()]).is_err());
+    }
+
+    #[test]
+    fn test_revocation_scope() {
+        let jwt = WsAuthState::new(&claims("u", 1_000, None), None);
+        let key = WsAuthState::new(&claims("u", 1_000, Some("api_key")), None);
+
+        let all = revocation("u", RevocationScope::AllTokens);
+        assert!(jwt.is_revoked_by(&all, "u"));
+        assert!(key.is_revoked_by(&all, "u"));
+        assert!(!jwt.is_revoked_by(&all, "someone-else"));
+
+        let keys = revocation("u", RevocationScope::ApiKeys);
+        assert!(!jwt.is_revoked_by(&keys, "u"));
+        assert!(key.is_revoked_by(&keys, "u"));
+
+        let impersonated = WsAuthState::new(
+            &Claims {
+                act: Some(actor()),
+                ..claims("u", 1_000, None)
+            },
+            None,
+        );
+        let ended = revocation("u", RevocationScope::Impersonation);
+        assert!(impersonated.is_revoked_by(&ended, "u"));
+        assert!(!jwt.is_revoked_by(&ended, "u"));
+    }
+
+    #[test]
+    fn test_refresh_keeps_impersonation() {
+        let future = Utc::now().timestamp() + 3600;
+        let impersonated = || Claims {
+            act: Some(actor()),
+            ..claims("u", future, None)
+        };
+
+        let mut own = WsAuthState::new(&claims("u", 1_000, None), None);
+        assert!(own.refresh(&impersonated(), "u").is_err());
+
+        let mut auth = WsAuthState::new(&impersonated(), None);
+        assert!(auth.refresh(&claims("u", future, None), "u").is_err());
+        auth.refresh(&impersonated(), "u").unwrap();
+        assert_eq!(auth.impersonation(), Some(&actor()));
+    }
+
+    #[test]
+    fn test_deserialize_system_auth_commands() {
+        let cmd: WsCommand = serde_json::from_str(
+            r#"{"channel":"system","type":"auth.refresh","id":"r1","token":"abc"}"#,
+        )
+        .unwrap();
+        assert!(matches!(
+            cmd,
+            WsCommand::System(SystemWsCommand::AuthRefresh { ref token, .. }) if token == "abc"
+        ));
+
+        let cmd: WsCommand = serde_json::from_str(
+            r#"{"channel":"system","type":"auth.bind_sessions","session_ids":["s1"]}"#,
+        )
+        .unwrap();
+        assert!(matches!(
+            cmd,
+            WsCommand::System(SystemWsCommand::AuthBindSessions { .. })
+        ));
+    }
+
+    #[test]
+    fn test_serialize_auth_revoked() {
+        let event = WsEvent::System(SystemWsEvent::AuthRevoked {
+            reason: "role changed".to_string(),
+        });
+        let json = serde_json::to_string(&event).unwrap();
+        assert!(json.contains(r#""channel":"system""#));
+        assert!(json.contains(r#""type":"auth.revoked""#));
+        assert!(json.contains(r#""reason":"role changed""#));
+    }
+}
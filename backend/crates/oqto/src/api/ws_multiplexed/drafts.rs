@@ -0,0 +1,292 @@
+//! Draft channel: prompt drafts shared between a user's connections (see
+//! `crate::prompt_drafts`).
+//!
+//! A connection `open`s the draft of a session to get its state and be told
+//! about edits from the user's other connections; `update` merges edits and
+//! relays them. Drafts are cleared once a prompt is sent to the session.
+
+use std::sync::Weak;
+
+use super::*;
+use crate::prompt_drafts::DraftOp;
+
+type ConnState = tokio::sync::Mutex<WsConnectionState>;
+
+/// Connections watching each (user, session) draft. Entries of closed
+/// connections are pruned as they are found.
+static WATCHERS: Lazy<std::sync::Mutex<HashMap<(String, String), Vec<Weak<ConnState>>>>> =
+    Lazy::new(|| std::sync::Mutex::new(HashMap::new()));
+
+/// Draft channel commands.
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum DraftWsCommand {
+    /// Get the draft of a session and follow edits to it.
+    Open {
+        #[serde(skip_serializing_if = "Option::is_none")]
+        id: Option<String>,
+        session_id: String,
+    },
+    /// Apply edits to the draft.
+    Update {
+        #[serde(skip_serializing_if = "Option::is_none")]
+        id: Option<String>,
+        session_id: String,
+        ops: Vec<DraftOp>,
+    },
+    /// Discard the draft.
+    Clear {
+        #[serde(skip_serializing_if = "Option::is_none")]
+        id: Option<String>,
+        session_id: String,
+    },
+    /// Stop following edits to the draft.
+    Close {
+        #[serde(skip_serializing_if = "Option::is_none")]
+        id: Option<String>,
+        session_id: String,
+    },
+}
+
+impl DraftWsCommand {
+    pub(super) fn id(&self) -> Option<String> {
+        match self {
+            Self::Open { id, .. }
+            | Self::Update { id, .. }
+            | Self::Clear { id, .. }
+            | Self::Close { id, .. } => id.clone(),
+        }
+    }
+
+    pub(super) fn session_id(&self) -> &str {
+        match self {
+            Self::Open { session_id, .. }
+            | Self::Update { session_id, .. }
+            | Self::Clear { session_id, .. }
+            | Self::Close { session_id, .. } => session_id,
+        }
+    }
+
+    pub(super) fn label(&self) -> &'static str {
+        match self {
+            Self::Open { .. } => "draft.open",
+            Self::Update { .. } => "draft.update",
+            Self::Clear { .. } => "draft.clear",
+            Self::Close { .. } => "draft.close",
+        }
+    }
+}
+
+/// Draft channel events.
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum DraftWsEvent {
+    /// Current draft, in reply to `open`.
+    Snapshot {
+        #[serde(skip_serializing_if = "Option::is_none")]
+        id: Option<String>,
+        session_id: String,
+        state: crate::prompt_drafts::DraftState,
+        text: String,
+    },
+    /// Edits were applied.
+    Ack {
+        #[serde(skip_serializing_if = "Option::is_none")]
+        id: Option<String>,
+        session_id: String,
+        version: u64,
+    },
+    /// Another connection of the user edited the draft.
+    Updated {
+        session_id: String,
+        ops: Vec<DraftOp>,
+        version: u64,
+    },
+    /// The draft was sent or discarded.
+    Cleared { session_id: String },
+    /// The command failed; after a failed `update`, `open` the draft again.
+    Error {
+        #[serde(skip_serializing_if = "Option::is_none")]
+        id: Option<String>,
+        session_id: String,
+        error: String,
+    },
+}
+
+pub(super) async fn handle_draft_command(
+    cmd: DraftWsCommand,
+    user_id: &str,
+    state: &AppState,
+    conn_state: Arc<ConnState>,
+) -> Option<WsEvent> {
+    let id = cmd.id();
+    let session_id = cmd.session_id().to_string();
+    let error = |error: String| {
+        Some(WsEvent::Draft(DraftWsEvent::Error {
+            id: id.clone(),
+            session_id: session_id.clone(),
+            error,
+        }))
+    };
+    let Some(drafts) = state.prompt_drafts.as_ref() else {
+        return error("Prompt drafts are not available".to_string());
+    };
+
+    match cmd {
+        DraftWsCommand::Open { .. } => match drafts.get(user_id, &session_id).await {
+            Ok(doc) => {
+                watch(user_id, &session_id, &conn_state);
+                Some(WsEvent::Draft(DraftWsEvent::Snapshot {
+                    id,
+                    session_id,
+                    text: doc.text(),
+                    state: doc.state(),
+                }))
+            }
+            Err(e) => error(format!("Failed to load draft: {e:#}")),
+        },
+        DraftWsCommand::Update { ops, .. } => {
+            match drafts.apply(user_id, &session_id, &ops).await {
+                Ok(doc) => {
+                    let version = doc.version();
+                    watch(user_id, &session_id, &conn_state);
+                    broadcast(
+                        user_id,
+                        &session_id,
+                        Some(&conn_state),
+                        DraftWsEvent::Updated {
+                            session_id: session_id.clone(),
+                            ops,
+                            version,
+                        },
+                    )
+                    .await;
+                    Some(WsEvent::Draft(DraftWsEvent::Ack {
+                        id,
+                        session_id,
+                        version,
+                    }))
+                }
+                Err(e) => error(format!("{e:#}")),
+            }
+        }
+        DraftWsCommand::Clear { .. } => match clear(user_id, &session_id, state).await {
+            Ok(()) => None,
+            Err(e) => error(format!("Failed to clear draft: {e:#}")),
+        },
+        DraftWsCommand::Close { .. } => {
+            unwatch(user_id, &session_id, &conn_state);
+            None
+        }
+    }
+}
+
+/// Discard a user's draft of a session and tell all their connections.
+pub(super) async fn clear(user_id: &str, session_id: &str, state: &AppState) -> anyhow::Result<()> {
+    let Some(drafts) = state.prompt_drafts.as_ref() else {
+        return Ok(());
+    };
+    if drafts.clear(user_id, session_id).await? {
+        broadcast(
+            user_id,
+            session_id,
+            None,
+            DraftWsEvent::Cleared {
+                session_id: session_id.to_string(),
+            },
+        )
+        .await;
+    }
+    Ok(())
+}
+
+/// Session whose draft a command sends, if it sends one.
+pub(super) fn sent_draft(cmd: &WsCommand) -> Option<String> {
+    use oqto_protocol::commands::CommandPayload;
+
+    match cmd {
+        WsCommand::Agent(agent_cmd) => matches!(
+            agent_cmd.payload,
+            CommandPayload::Prompt { .. }
+                | CommandPayload::FollowUp { .. }
+                | CommandPayload::Steer { .. }
+                | CommandPayload::Interject { .. }
+        )
+        .then(|| agent_cmd.session_id.clone()),
+        _ => None,
+    }
+}
+
+/// Clear the draft a command sent once it succeeded.
+pub(super) async fn clear_sent(
+    session_id: Option<String>,
+    response: &Option<WsEvent>,
+    user_id: &str,
+    state: &AppState,
+) {
+    let Some(session_id) = session_id else {
+        return;
+    };
+    if !session_shares::succeeded(response) {
+        return;
+    }
+    if let Err(e) = clear(user_id, &session_id, state).await {
+        warn!(session_id = %session_id, "Failed to clear sent prompt draft: {e:#}");
+    }
+}
+
+fn watch(user_id: &str, session_id: &str, conn_state: &Arc<ConnState>) {
+    let mut watchers = WATCHERS.lock().unwrap();
+    let entries = watchers
+        .entry((user_id.to_string(), session_id.to_string()))
+        .or_default();
+    entries.retain(|conn| conn.strong_count() > 0);
+    if !entries
+        .iter()
+        .any(|conn| std::ptr::eq(conn.as_ptr(), Arc::as_ptr(conn_state)))
+    {
+        entries.push(Arc::downgrade(conn_state));
+    }
+}
+
+fn unwatch(user_id: &str, session_id: &str, conn_state: &Arc<ConnState>) {
+    let key = (user_id.to_string(), session_id.to_string());
+    let mut watchers = WATCHERS.lock().unwrap();
+    if let Some(entries) = watchers.get_mut(&key) {
+        entries.retain(|conn| {
+            conn.strong_count() > 0 && !std::ptr::eq(conn.as_ptr(), Arc::as_ptr(conn_state))
+        });
+        if entries.is_empty() {
+            watchers.remove(&key);
+        }
+    }
+}
+
+/// Send an event to every connection watching the draft except `from`.
+async fn broadcast(
+    user_id: &str,
+    session_id: &str,
+    from: Option<&Arc<ConnState>>,
+    event: DraftWsEvent,
+) {
+    let connections: Vec<Arc<ConnState>> = {
+        let key = (user_id.to_string(), session_id.to_string());
+        let mut watchers = WATCHERS.lock().unwrap();
+        let Some(entries) = watchers.get_mut(&key) else {
+            return;
+        };
+        entries.retain(|conn| conn.strong_count() > 0);
+        let connections = entries.iter().filter_map(Weak::upgrade).collect();
+        if entries.is_empty() {
+            watchers.remove(&key);
+        }
+        connections
+    };
+    for conn in connections {
+        if from.is_some_and(|from| Arc::ptr_eq(&conn, from)) {
+            continue;
+        }
+        let event_tx = conn.lock().await.event_tx.clone();
+        let _ = event_tx.send(WsEvent::Draft(event.clone()));
+    }
+}
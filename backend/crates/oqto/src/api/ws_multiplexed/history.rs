@@ -57,7 +57,24 @@ pub(super) async fn handle_hstry_command(
         )
         .await
         {
-            Ok(Some(messages)) => {
+            Ok(Some(mut messages)) => {
+                match crate::api::handlers::private_messages::hidden_from(
+                    state,
+                    &session_id,
+                    user_id,
+                )
+                .await
+                {
+                    Ok(hidden) => {
+                        crate::private_messages::elide(&mut messages, &hidden);
+                    }
+                    Err(err) => {
+                        return Some(WsEvent::Hstry(HstryWsEvent::Error {
+                            id,
+                            error: err.to_string(),
+                        }));
+                    }
+                }
                 let data = serde_json::to_value(&messages).unwrap_or(Value::Null);
                 return Some(WsEvent::Hstry(HstryWsEvent::Result { id, data }));
             }
@@ -90,6 +107,7 @@ pub(super) async fn handle_hstry_command(
                 workspace_id: None,
                 cwd: None,
                 limit: limit.unwrap_or(50) as usize,
+                filters: Default::default(),
             },
         )
         .await
@@ -102,7 +120,22 @@ pub(super) async fn handle_hstry_command(
                 }));
             }
         };
-        let data = serde_json::to_value(response.results).unwrap_or(Value::Null);
+        let results = match crate::api::handlers::private_messages::visible_results(
+            state,
+            user_id,
+            response.results,
+        )
+        .await
+        {
+            Ok(results) => results,
+            Err(err) => {
+                return Some(WsEvent::Hstry(HstryWsEvent::Error {
+                    id,
+                    error: err.to_string(),
+                }));
+            }
+        };
+        let data = serde_json::to_value(results).unwrap_or(Value::Null);
         Some(WsEvent::Hstry(HstryWsEvent::Result { id, data }))
     }
 }
@@ -128,6 +161,8 @@ pub(super) async fn handle_trx_command(
             preferred_username: None,
             roles: vec![],
             role: None,
+            act: None,
+            scope: None,
         },
     };
 
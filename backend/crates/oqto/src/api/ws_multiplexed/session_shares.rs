@@ -0,0 +1,601 @@
+//! Agent commands on sessions shared between users (see
+//! `crate::session_shares`).
+//!
+//! A participant's commands run on the owner's runner: `session.create`
+//! attaches to the session instead of creating it, `session.close` only
+//! detaches, and lifecycle commands are left to the owner. Because the
+//! runner does not echo user messages, prompts sent in a shared session are
+//! tagged with the sender's name and fanned out to every other connection
+//! attached to it.
+
+use std::sync::Weak;
+
+use oqto_protocol::commands::{Command, CommandPayload};
+
+use super::*;
+use crate::session_shares::{SessionShare, ShareAccess};
+use crate::shared_workspace::sanitize_display_name;
+
+type ConnState = tokio::sync::Mutex<WsConnectionState>;
+
+/// Connections attached to each session, for fanning out participants'
+/// prompts. Entries of closed connections are pruned as they are found.
+static ATTACHED: Lazy<std::sync::Mutex<HashMap<String, Vec<Attached>>>> =
+    Lazy::new(|| std::sync::Mutex::new(HashMap::new()));
+
+struct Attached {
+    user_id: String,
+    conn: Weak<ConnState>,
+}
+
+/// What running a command in someone else's session requires.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Needs {
+    Read,
+    Write,
+    /// Only the owner may run it.
+    Owner,
+}
+
+fn command_needs(payload: &CommandPayload) -> Needs {
+    match payload {
+        CommandPayload::SessionCreate { .. }
+        | CommandPayload::SessionClose
+        | CommandPayload::GetState
+        | CommandPayload::GetMessages
+        | CommandPayload::GetStats
+        | CommandPayload::GetModels { .. }
+        | CommandPayload::GetCommands
+        | CommandPayload::GetForkPoints => Needs::Read,
+        CommandPayload::Prompt { .. }
+        | CommandPayload::Steer { .. }
+        | CommandPayload::Interject { .. }
+        | CommandPayload::FollowUp { .. }
+        | CommandPayload::Abort
+        | CommandPayload::AbortRetry
+        | CommandPayload::InputResponse { .. }
+        | CommandPayload::SetModel { .. }
+        | CommandPayload::CycleModel
+        | CommandPayload::SetThinkingLevel { .. }
+        | CommandPayload::CycleThinkingLevel
+        | CommandPayload::SetAutoCompaction { .. }
+        | CommandPayload::SetAutoRetry { .. }
+        | CommandPayload::Compact { .. } => Needs::Write,
+        _ => Needs::Owner,
+    }
+}
+
+/// Error unless a participant with `access` may run a command needing
+/// `needs`.
+fn check_access(access: ShareAccess, needs: Needs) -> Result<(), String> {
+    match needs {
+        Needs::Read => Ok(()),
+        Needs::Write if access.can_write() => Ok(()),
+        Needs::Write => Err("Read-only access to this shared session".to_string()),
+        Needs::Owner => Err("Only the session owner can do this".to_string()),
+    }
+}
+
+/// Prefix a message with its sender, in the format the agent and the
+/// frontend already understand from shared workspaces.
+fn tag_message(display_name: &str, message: &str) -> String {
+    format!(
+        "@sender: {}\n{}",
+        sanitize_display_name(display_name),
+        message
+    )
+}
+
+/// How a command on a possibly shared session is handled.
+pub(super) enum Routed {
+    /// The command ran on a session shared with the caller.
+    Shared(Option<WsEvent>),
+    /// The caller's own session (or a shared workspace one): handle it as
+    /// usual, then run the follow-up, if any, on the response.
+    Own(Command, Option<FollowUp>),
+}
+
+/// Work left after a command on the caller's own session.
+pub(super) enum FollowUp {
+    /// Show the owner's message to the participants.
+    Fanout(UserMessage),
+    /// The owner deleted the session; stop sharing it.
+    ForgetShares {
+        session_id: String,
+        owner_user_id: String,
+    },
+}
+
+/// A user message to show to everyone else attached to a session.
+pub(super) struct UserMessage {
+    session_id: String,
+    sender_id: String,
+    sender_name: String,
+    /// Message as sent to the agent, sender tag included.
+    message: String,
+    client_id: Option<String>,
+}
+
+/// Route an agent command. Sessions shared with the caller run on their
+/// owner's runner here; everything else is returned for normal handling.
+pub(super) async fn route(
+    mut cmd: Command,
+    user_id: &str,
+    state: &AppState,
+    conn_state: &Arc<ConnState>,
+) -> Routed {
+    let Some(shares) = state.session_shares.as_ref() else {
+        return Routed::Own(cmd, None);
+    };
+    if cmd.session_id == "_system" {
+        return Routed::Own(cmd, None);
+    }
+
+    let share = match shares.get(&cmd.session_id, user_id).await {
+        Ok(share) => share,
+        Err(e) => {
+            warn!(session_id = %cmd.session_id, "Failed to look up session share: {e:#}");
+            None
+        }
+    };
+    if let Some(share) = share {
+        return Routed::Shared(handle_shared(cmd, share, user_id, state, conn_state).await);
+    }
+
+    match &cmd.payload {
+        CommandPayload::SessionCreate { .. } => {
+            attach(&cmd.session_id, user_id, conn_state);
+            Routed::Own(cmd, None)
+        }
+        CommandPayload::SessionDelete => {
+            let forget = FollowUp::ForgetShares {
+                session_id: cmd.session_id.clone(),
+                owner_user_id: user_id.to_string(),
+            };
+            Routed::Own(cmd, Some(forget))
+        }
+        CommandPayload::Prompt { .. }
+        | CommandPayload::Steer { .. }
+        | CommandPayload::Interject { .. }
+        | CommandPayload::FollowUp { .. } => {
+            // The owner's messages are attributed too once others take part.
+            if !shares.is_shared(&cmd.session_id).await.unwrap_or(false) {
+                return Routed::Own(cmd, None);
+            }
+            let message = tag_prompt(&mut cmd, user_id, state).await;
+            Routed::Own(cmd, message.map(FollowUp::Fanout))
+        }
+        _ => Routed::Own(cmd, None),
+    }
+}
+
+impl FollowUp {
+    /// Run after the command was handled.
+    pub(super) async fn run(
+        self,
+        state: &AppState,
+        response: &Option<WsEvent>,
+        conn_state: &Arc<ConnState>,
+    ) {
+        if !succeeded(response) {
+            return;
+        }
+        match self {
+            FollowUp::Fanout(message) => fanout(message, conn_state).await,
+            FollowUp::ForgetShares {
+                session_id,
+                owner_user_id,
+            } => {
+                if let Some(shares) = state.session_shares.as_ref()
+                    && let Err(e) = shares.delete_for_session(&session_id).await
+                {
+                    warn!(session_id = %session_id, "Failed to remove session shares: {e:#}");
+                }
+                detach_all(&session_id, &owner_user_id).await;
+            }
+        }
+    }
+}
+
+/// Run a participant's command on the owner's runner.
+async fn handle_shared(
+    mut cmd: Command,
+    share: SessionShare,
+    user_id: &str,
+    state: &AppState,
+    conn_state: &Arc<ConnState>,
+) -> Option<WsEvent> {
+    let session_id = cmd.session_id.clone();
+    let runner_id = cmd.runner_id.clone().unwrap_or_else(|| "local".to_string());
+    let (audit_label, _, _) = ws_command_summary(&WsCommand::Agent(cmd.clone()));
+    // Responses name the command as it was sent.
+    let label = serde_json::to_value(&cmd.payload)
+        .ok()
+        .and_then(|v| v.get("cmd").and_then(Value::as_str).map(str::to_string))
+        .unwrap_or_default();
+    let respond = |id: Option<String>, result: Result<Option<Value>, String>| {
+        Some(agent_response_with_runner(
+            &runner_id,
+            &session_id,
+            id,
+            &label,
+            result,
+        ))
+    };
+
+    if let Err(error) = check_access(share.access, command_needs(&cmd.payload)) {
+        warn!(
+            user_id = %user_id,
+            owner_user_id = %share.owner_user_id,
+            session_id = %session_id,
+            command = %label,
+            "Shared session command rejected"
+        );
+        return respond(cmd.id, Err(error));
+    }
+
+    let runner =
+        match resolve_runner_for_target(state, &share.owner_user_id, &ExecutionTarget::Personal)
+            .await
+        {
+            Ok(Some(runner)) => runner,
+            Ok(None) => return respond(cmd.id, Err("Owner's runner is not available".into())),
+            Err(e) => return respond(cmd.id, Err(format!("Owner's runner is not available: {e}"))),
+        };
+
+    if let Some(logger) = state.audit_logger.as_ref() {
+        let impersonation = conn_state.lock().await.impersonation.clone();
+        logger
+            .log_ws_command(
+                user_id,
+                impersonation.as_ref(),
+                &audit_label,
+                Some(&session_id),
+                None,
+            )
+            .await;
+    }
+
+    // Pin the session to the owner's runner and workspace, so the regular
+    // handler routes the command there.
+    let cwd = state
+        .session_targets
+        .get(&session_id)
+        .await
+        .ok()
+        .flatten()
+        .and_then(|record| record.workspace_path)
+        .map(std::path::PathBuf::from);
+    {
+        let mut guard = conn_state.lock().await;
+        guard
+            .session_runner_overrides
+            .insert(session_id.clone(), runner.clone());
+        guard
+            .pi_session_meta
+            .entry(session_id.clone())
+            .or_insert(PiSessionMeta {
+                scope: None,
+                cwd,
+                sandbox_profile: None,
+            });
+    }
+
+    match cmd.payload {
+        CommandPayload::SessionCreate {
+            mut last_seen_seq, ..
+        } => {
+            // Replayed events are not filtered; with anything private in the
+            // session the participant reloads the (elided) messages instead.
+            if last_seen_seq.is_some()
+                && !crate::api::handlers::private_messages::hidden_from(state, &session_id, user_id)
+                    .await
+                    .is_ok_and(|hidden| hidden.is_empty())
+            {
+                last_seen_seq = None;
+            }
+            if last_seen_seq.is_some()
+                && let Err(e) =
+                    admit_replay(state, conn_state, cmd.id.as_deref(), &session_id).await
+            {
+                return respond(cmd.id, Err(e));
+            }
+            let running = runner.agent_get_state(&session_id).await.is_ok();
+            if running {
+                subscribe(&runner, &runner_id, &session_id, last_seen_seq, conn_state).await;
+            }
+            attach(&session_id, user_id, conn_state);
+            info!(
+                user_id = %user_id,
+                owner_user_id = %share.owner_user_id,
+                session_id = %session_id,
+                running,
+                "Joined shared session"
+            );
+            respond(
+                cmd.id,
+                Ok(Some(serde_json::json!({
+                    "session_id": session_id,
+                    "shared": true,
+                    "owner_user_id": share.owner_user_id,
+                    "access": share.access,
+                    "running": running,
+                }))),
+            )
+        }
+        CommandPayload::SessionClose => {
+            detach(&session_id, conn_state).await;
+            respond(cmd.id, Ok(None))
+        }
+        CommandPayload::Prompt { .. }
+        | CommandPayload::Steer { .. }
+        | CommandPayload::Interject { .. }
+        | CommandPayload::FollowUp { .. } => {
+            let message = tag_prompt(&mut cmd, user_id, state).await;
+            info!(
+                user_id = %user_id,
+                owner_user_id = %share.owner_user_id,
+                session_id = %session_id,
+                command = %label,
+                "Shared session prompt"
+            );
+            let response = agent::handle_agent_command(
+                cmd,
+                user_id,
+                state,
+                Some(&runner),
+                Arc::clone(conn_state),
+            )
+            .await;
+            if let Some(message) = message
+                && succeeded(&response)
+            {
+                fanout(message, conn_state).await;
+            }
+            response
+        }
+        _ => {
+            agent::handle_agent_command(cmd, user_id, state, Some(&runner), Arc::clone(conn_state))
+                .await
+        }
+    }
+}
+
+/// Tag the message of a prompt, steer or follow-up with the sender, and
+/// return it for fanning out.
+async fn tag_prompt(cmd: &mut Command, user_id: &str, state: &AppState) -> Option<UserMessage> {
+    let display_name = state
+        .users
+        .get_user(user_id)
+        .await
+        .ok()
+        .flatten()
+        .map(|u| u.display_name)
+        .unwrap_or_else(|| user_id.to_string());
+    let (message, client_id) = match &mut cmd.payload {
+        CommandPayload::Prompt {
+            message, client_id, ..
+        }
+        | CommandPayload::Steer { message, client_id }
+        | CommandPayload::Interject { message, client_id }
+        | CommandPayload::FollowUp { message, client_id } => (message, client_id.clone()),
+        _ => return None,
+    };
+    if message.trim().is_empty() {
+        return None;
+    }
+    *message = tag_message(&display_name, message);
+    Some(UserMessage {
+        session_id: cmd.session_id.clone(),
+        sender_id: user_id.to_string(),
+        sender_name: display_name,
+        message: message.clone(),
+        client_id,
+    })
+}
+
+pub(super) fn succeeded(response: &Option<WsEvent>) -> bool {
+    match response {
+        Some(WsEvent::Agent(event)) => matches!(
+            &event.payload,
+            oqto_protocol::events::EventPayload::Response(r) if r.success
+        ),
+        // Fire-and-forget commands answer with nothing.
+        None => true,
+        _ => false,
+    }
+}
+
+/// Subscribe this connection to the session's events on `runner`.
+async fn subscribe(
+    runner: &RunnerClient,
+    runner_id: &str,
+    session_id: &str,
+    last_seen_seq: Option<u64>,
+    conn_state: &Arc<ConnState>,
+) {
+    let mut guard = conn_state.lock().await;
+    if guard.pi_subscriptions.contains(session_id) {
+        return;
+    }
+    guard.subscribed_sessions.insert(session_id.to_string());
+    guard.pi_subscriptions.insert(session_id.to_string());
+    let event_tx = guard.event_tx.clone();
+    let (ready_tx, ready_rx) = oneshot::channel::<()>();
+    let forwarder = {
+        let runner = runner.clone();
+        let runner_id = runner_id.to_string();
+        let sid = session_id.to_string();
+        let conn_state = Arc::clone(conn_state);
+        tokio::spawn(async move {
+            if let Err(e) = forward_pi_events(
+                &runner,
+                &sid,
+                event_tx,
+                conn_state,
+                Some(ready_tx),
+                runner_id,
+                last_seen_seq,
+            )
+            .await
+            {
+                error!("Event forwarding error for shared session {}: {:?}", sid, e);
+            }
+        })
+    };
+    guard
+        .pi_forwarders
+        .insert(session_id.to_string(), forwarder);
+    drop(guard);
+
+    if tokio::time::timeout(Duration::from_secs(5), ready_rx)
+        .await
+        .is_err()
+    {
+        warn!(
+            "Timed out waiting for event subscription on shared session {}",
+            session_id
+        );
+    }
+}
+
+fn attach(session_id: &str, user_id: &str, conn_state: &Arc<ConnState>) {
+    let mut attached = ATTACHED.lock().unwrap();
+    let entries = attached.entry(session_id.to_string()).or_default();
+    entries.retain(|entry| entry.conn.strong_count() > 0);
+    if !entries
+        .iter()
+        .any(|entry| std::ptr::eq(entry.conn.as_ptr(), Arc::as_ptr(conn_state)))
+    {
+        entries.push(Attached {
+            user_id: user_id.to_string(),
+            conn: Arc::downgrade(conn_state),
+        });
+    }
+}
+
+/// Stop forwarding a session's events to this connection.
+async fn detach(session_id: &str, conn_state: &Arc<ConnState>) {
+    {
+        let mut attached = ATTACHED.lock().unwrap();
+        if let Some(entries) = attached.get_mut(session_id) {
+            entries.retain(|entry| {
+                entry.conn.strong_count() > 0
+                    && !std::ptr::eq(entry.conn.as_ptr(), Arc::as_ptr(conn_state))
+            });
+            if entries.is_empty() {
+                attached.remove(session_id);
+            }
+        }
+    }
+    let mut guard = conn_state.lock().await;
+    guard.subscribed_sessions.remove(session_id);
+    guard.pi_subscriptions.remove(session_id);
+    guard.session_runner_overrides.remove(session_id);
+    guard.pi_session_meta.remove(session_id);
+    if let Some(handle) = guard.pi_forwarders.remove(session_id) {
+        handle.abort();
+    }
+    if let Some(handle) = guard.response_watchdogs.remove(session_id) {
+        handle.abort();
+    }
+}
+
+/// Connections of `user_id` (or of anyone, for None) attached to a session.
+fn attached_connections(session_id: &str, user_id: Option<&str>) -> Vec<Arc<ConnState>> {
+    let attached = ATTACHED.lock().unwrap();
+    attached
+        .get(session_id)
+        .into_iter()
+        .flatten()
+        .filter(|entry| user_id.is_none_or(|user_id| entry.user_id == user_id))
+        .filter_map(|entry| entry.conn.upgrade())
+        .collect()
+}
+
+/// Detach a participant from a session they lost access to.
+pub(crate) async fn detach_participant(session_id: &str, user_id: &str) {
+    for conn in attached_connections(session_id, Some(user_id)) {
+        detach(session_id, &conn).await;
+    }
+}
+
+/// Detach the participants of a deleted session. The owner's connections
+/// already dropped it.
+async fn detach_all(session_id: &str, owner_user_id: &str) {
+    let participants: Vec<_> = {
+        let attached = ATTACHED.lock().unwrap();
+        attached
+            .get(session_id)
+            .into_iter()
+            .flatten()
+            .filter(|entry| entry.user_id != owner_user_id)
+            .filter_map(|entry| entry.conn.upgrade())
+            .collect()
+    };
+    for conn in participants {
+        detach(session_id, &conn).await;
+    }
+    ATTACHED.lock().unwrap().remove(session_id);
+}
+
+/// Show a user message on every other connection attached to the session.
+async fn fanout(message: UserMessage, from: &Arc<ConnState>) {
+    let sender = oqto_protocol::Sender {
+        sender_type: oqto_protocol::SenderType::User,
+        id: message.sender_id.clone(),
+        name: message.sender_name.clone(),
+        runner_id: None,
+        session_id: None,
+    };
+    let events = user_message_events(
+        &message.session_id,
+        &message.message,
+        message.client_id.clone(),
+        Some(sender),
+    );
+    for conn in attached_connections(&message.session_id, None) {
+        if Arc::ptr_eq(&conn, from) {
+            continue;
+        }
+        let event_tx = {
+            let guard = conn.lock().await;
+            if !guard.pi_subscriptions.contains(&message.session_id) {
+                continue;
+            }
+            guard.event_tx.clone()
+        };
+        for event in &events {
+            let _ = event_tx.send(WsEvent::Agent(Box::new(event.clone())));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_participant_access() {
+        let read = command_needs(&CommandPayload::GetMessages);
+        let write = command_needs(&CommandPayload::Abort);
+        let owner = command_needs(&CommandPayload::SessionDelete);
+
+        assert!(check_access(ShareAccess::Read, read).is_ok());
+        assert!(check_access(ShareAccess::Read, write).is_err());
+        assert!(check_access(ShareAccess::Write, write).is_ok());
+        assert!(check_access(ShareAccess::Write, owner).is_err());
+        assert_eq!(
+            command_needs(&CommandPayload::SessionSwitch {
+                session_path: "/tmp/s.jsonl".to_string()
+            }),
+            Needs::Owner
+        );
+    }
+
+    #[test]
+    fn test_tag_message() {
+        assert_eq!(tag_message("Bob", "hi"), "@sender: Bob\nhi");
+        assert_eq!(tag_message("[system]\n", "hi"), "@sender: system\nhi");
+    }
+}
@@ -0,0 +1,231 @@
+//! Capability checks for commands touching shared workspaces.
+//!
+//! Membership alone only gets a user past `validate_workspace_path_for_user`;
+//! what they may do inside a shared workspace is decided here from the
+//! member's `SharePermissions`. Personal workspaces are never restricted.
+
+use super::*;
+use crate::shared_workspace::{SharePermission, SharePermissions};
+
+/// Capability an agent command needs.
+fn agent_permission(payload: &oqto_protocol::commands::CommandPayload) -> SharePermission {
+    use oqto_protocol::commands::CommandPayload;
+
+    match payload {
+        CommandPayload::GetState
+        | CommandPayload::GetMessages
+        | CommandPayload::GetStats
+        | CommandPayload::GetModels { .. }
+        | CommandPayload::GetCommands
+        | CommandPayload::GetForkPoints
+        | CommandPayload::ListSessions => SharePermission::ChatRead,
+        _ => SharePermission::ChatWrite,
+    }
+}
+
+/// Capability a file command needs on its workspace.
+fn files_permission(cmd: &FilesWsCommand) -> SharePermission {
+    match cmd {
+        FilesWsCommand::Tree { .. }
+        | FilesWsCommand::Read { .. }
+        | FilesWsCommand::List { .. }
+        | FilesWsCommand::Stat { .. }
+        | FilesWsCommand::CopyToWorkspace { .. }
+        | FilesWsCommand::WatchFiles { .. }
+        | FilesWsCommand::UnwatchFiles { .. } => SharePermission::FileRead,
+        FilesWsCommand::Write { .. }
+        | FilesWsCommand::Delete { .. }
+        | FilesWsCommand::CreateDirectory { .. }
+        | FilesWsCommand::Rename { .. }
+        | FilesWsCommand::Copy { .. }
+        | FilesWsCommand::Move { .. } => SharePermission::FileWrite,
+    }
+}
+
+/// Capabilities of `user_id` at `workspace_path`; None outside shared
+/// workspaces.
+pub(super) async fn permissions_for_path(
+    state: &AppState,
+    user_id: &str,
+    workspace_path: &str,
+) -> Result<Option<SharePermissions>, String> {
+    let Some(sw_service) = state.shared_workspaces.as_ref() else {
+        return Ok(None);
+    };
+    // Resolve the same way `validate_workspace_path_for_user` does so a
+    // relative or `..`-laden path cannot dodge the prefix match.
+    let resolved = match state.linux_users.as_ref() {
+        Some(lu) => {
+            let user_home =
+                std::path::PathBuf::from(format!("/home/{}", lu.linux_username(user_id)));
+            resolve_workspace_path_for_validation(workspace_path, &user_home)
+                .to_string_lossy()
+                .into_owned()
+        }
+        None => workspace_path.to_string(),
+    };
+    sw_service
+        .permissions_for_path(&resolved, user_id)
+        .await
+        .map_err(|e| format!("Failed to check workspace permissions: {e}"))
+}
+
+/// Capabilities of `user_id` in the shared workspace a session runs in;
+/// None for personal or unknown sessions.
+async fn permissions_for_session(
+    state: &AppState,
+    user_id: &str,
+    session_id: &str,
+) -> Result<Option<SharePermissions>, String> {
+    let Some(sw_service) = state.shared_workspaces.as_ref() else {
+        return Ok(None);
+    };
+    let record = match state.session_targets.get(session_id).await {
+        Ok(Some(record)) => record,
+        Ok(None) => return Ok(None),
+        Err(e) => return Err(format!("Failed to resolve session target: {e}")),
+    };
+    match (record.scope, record.workspace_id, record.workspace_path) {
+        (SessionTargetScope::SharedWorkspace, Some(workspace_id), _) => sw_service
+            .permissions_for_workspace(&workspace_id, user_id)
+            .await
+            .map(Some)
+            .map_err(|e| format!("Failed to check workspace permissions: {e}")),
+        // Older rows may be stored as personal despite a shared path.
+        (_, _, Some(workspace_path)) => permissions_for_path(state, user_id, &workspace_path).await,
+        _ => Ok(None),
+    }
+}
+
+/// Error unless the (optional) grant allows `permission`.
+pub(super) fn require(
+    permissions: Option<SharePermissions>,
+    permission: SharePermission,
+) -> Result<(), String> {
+    match permissions {
+        Some(p) if !p.allows(permission) => Err(format!(
+            "Access denied: missing '{permission}' permission in this shared workspace"
+        )),
+        _ => Ok(()),
+    }
+}
+
+/// Check an agent or file command against the caller's share permissions.
+/// Terminal commands are checked when the terminal is opened (see
+/// `terminal::handle_terminal_command`).
+pub(super) async fn check_command(
+    cmd: &WsCommand,
+    user_id: &str,
+    state: &AppState,
+) -> Option<WsEvent> {
+    state.shared_workspaces.as_ref()?;
+
+    match cmd {
+        WsCommand::Agent(agent_cmd) => {
+            use oqto_protocol::commands::CommandPayload;
+
+            let permission = agent_permission(&agent_cmd.payload);
+            let permissions = match &agent_cmd.payload {
+                CommandPayload::SessionCreate { config, .. } => match config.cwd.as_deref() {
+                    Some(cwd) => permissions_for_path(state, user_id, cwd).await,
+                    None => Ok(None),
+                },
+                _ => permissions_for_session(state, user_id, &agent_cmd.session_id).await,
+            };
+            let result = permissions.and_then(|p| require(p, permission));
+            let Err(error) = result else {
+                return None;
+            };
+            let (label, _, _) = ws_command_summary(cmd);
+            warn!(
+                user_id = %user_id,
+                session_id = %agent_cmd.session_id,
+                command = %label,
+                "Agent command rejected by share permissions"
+            );
+            Some(agent_response_with_runner(
+                agent_cmd.runner_id.as_deref().unwrap_or("local"),
+                &agent_cmd.session_id,
+                agent_cmd.id.clone(),
+                label.strip_prefix("agent.").unwrap_or(&label),
+                Err(error),
+            ))
+        }
+        WsCommand::Files(files_cmd) => {
+            let mut checks = Vec::with_capacity(2);
+            let (_, _, workspace_path) = ws_command_summary(cmd);
+            if let Some(path) = workspace_path {
+                checks.push((path, files_permission(files_cmd)));
+            }
+            if let FilesWsCommand::CopyToWorkspace {
+                target_workspace_path,
+                ..
+            } = files_cmd
+            {
+                checks.push((target_workspace_path.clone(), SharePermission::FileWrite));
+            }
+
+            for (path, permission) in checks {
+                let result = permissions_for_path(state, user_id, &path)
+                    .await
+                    .and_then(|p| require(p, permission));
+                if let Err(error) = result {
+                    warn!(
+                        user_id = %user_id,
+                        workspace_path = %path,
+                        permission = %permission,
+                        "File command rejected by share permissions"
+                    );
+                    return Some(WsEvent::Files(FilesWsEvent::Error {
+                        id: ws_command_id(cmd),
+                        error,
+                    }));
+                }
+            }
+            None
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn files_permission_splits_reads_and_writes() {
+        let read = FilesWsCommand::Stat {
+            id: None,
+            path: "a.txt".to_string(),
+            workspace_path: None,
+        };
+        let write = FilesWsCommand::Delete {
+            id: None,
+            path: "a.txt".to_string(),
+            recursive: false,
+            workspace_path: None,
+        };
+        assert_eq!(files_permission(&read), SharePermission::FileRead);
+        assert_eq!(files_permission(&write), SharePermission::FileWrite);
+    }
+
+    #[test]
+    fn require_only_restricts_shared_workspaces() {
+        let viewer = SharePermissions {
+            chat_read: true,
+            file_read: true,
+            ..Default::default()
+        };
+        assert!(require(None, SharePermission::FileWrite).is_ok());
+        assert!(require(Some(viewer), SharePermission::FileRead).is_ok());
+        let err = require(Some(viewer), SharePermission::ChatWrite).unwrap_err();
+        assert!(err.contains("chat_write"));
+        assert!(
+            require(
+                Some(SharePermissions::ALL),
+                SharePermission::TerminalControl
+            )
+            .is_ok()
+        );
+    }
+}
@@ -1,11 +1,23 @@
 //! Extracted channel handlers from ws_multiplexed.
 
 use super::*;
+use crate::shared_workspace::SharePermission;
 
 fn terminal_binding_matches(existing: &TerminalSession, user_id: &str, session_id: &str) -> bool {
     existing.owner_user_id == user_id && existing.session_id == session_id
 }
 
+fn view_only_error(id: Option<String>, terminal_id: String) -> WsEvent {
+    WsEvent::Terminal(TerminalWsEvent::Error {
+        id,
+        terminal_id: Some(terminal_id),
+        error: format!(
+            "Terminal is view-only: missing '{}' permission in this shared workspace",
+            SharePermission::TerminalControl
+        ),
+    })
+}
+
 pub(super) async fn handle_terminal_command(
     cmd: TerminalWsCommand,
     user_id: &str,
@@ -60,6 +72,28 @@ pub(super) async fn handle_terminal_command(
                 }));
             }
 
+            // Opening needs `terminal_view` in shared workspaces; without
+            // `terminal_control` the terminal is view-only.
+            let permissions = match share_permissions::permissions_for_path(
+                state,
+                user_id,
+                &session.workspace_path,
+            )
+            .await
+            .and_then(|p| share_permissions::require(p, SharePermission::TerminalView).map(|()| p))
+            {
+                Ok(permissions) => permissions,
+                Err(err) => {
+                    return Some(WsEvent::Terminal(TerminalWsEvent::Error {
+                        id,
+                        terminal_id,
+                        error: err,
+                    }));
+                }
+            };
+            let can_control =
+                share_permissions::require(permissions, SharePermission::TerminalControl).is_ok();
+
             let terminal_id = terminal_id.unwrap_or_else(|| uuid::Uuid::new_v4().to_string());
             let state_guard = conn_state.lock().await;
             if let Some(existing) = state_guard.terminal_sessions.get(&terminal_id) {
@@ -125,6 +159,7 @@ pub(super) async fn handle_terminal_command(
                     owner_user_id: user_id.to_string(),
                     session_id: session.id.clone(),
                     workspace_path: Some(session_workspace_path),
+                    can_control,
                     command_tx,
                     task,
                 },
@@ -149,6 +184,9 @@ pub(super) async fn handle_terminal_command(
                         error: "Terminal ownership mismatch".into(),
                     }));
                 }
+                if !session.can_control {
+                    return Some(view_only_error(id, terminal_id));
+                }
                 let _ = session.command_tx.send(TerminalSessionCommand::Input(data));
                 None
             } else {
@@ -174,6 +212,9 @@ pub(super) async fn handle_terminal_command(
                         error: "Terminal ownership mismatch".into(),
                     }));
                 }
+                if !session.can_control {
+                    return Some(view_only_error(id, terminal_id));
+                }
                 let _ = session
                     .command_tx
                     .send(TerminalSessionCommand::Resize { cols, rows });
@@ -223,6 +264,7 @@ mod tests {
             owner_user_id: "user-a".to_string(),
             session_id: "ses-1".to_string(),
             workspace_path: Some("/home/user-a/ws".to_string()),
+            can_control: true,
             command_tx: tx,
             task: tokio::spawn(async {}),
         };
@@ -236,6 +278,7 @@ mod tests {
             owner_user_id: "user-a".to_string(),
             session_id: "ses-1".to_string(),
             workspace_path: Some("/home/user-a/ws".to_string()),
+            can_control: true,
             command_tx: tx,
             task: tokio::spawn(async {}),
         };
@@ -12,6 +12,20 @@ use sha2::{Digest, Sha256};
 
 const API_KEY_PREFIX: &str = "octo_sk_";
 
+/// Scope for safe requests (GET, HEAD, OPTIONS).
+pub const SCOPE_READ: &str = "read";
+/// Scope for requests that can change state, WebSockets included.
+pub const SCOPE_WRITE: &str = "write";
+/// Scope keeping an admin's admin role; without it, the key acts as a
+/// regular user.
+pub const SCOPE_ADMIN: &str = "admin";
+
+/// Every scope a key can carry, in canonical order.
+pub const SCOPES: &[&str] = &[SCOPE_READ, SCOPE_WRITE, SCOPE_ADMIN];
+
+/// Scopes of a key created without any.
+pub const DEFAULT_SCOPES: &[&str] = &[SCOPE_READ, SCOPE_WRITE];
+
 /// Generate a new API key and its prefix.
 pub fn generate_api_key() -> (String, String) {
     let suffix = loop {
@@ -28,6 +42,30 @@ pub fn generate_api_key() -> (String, String) {
     (full, prefix)
 }
 
+/// Validate requested scopes and put them in canonical order; none
+/// requested means [`DEFAULT_SCOPES`]. `write` implies `read`.
+pub fn normalize_scopes(requested: Option<&[String]>) -> Result<Vec<String>> {
+    let Some(requested) = requested else {
+        return Ok(DEFAULT_SCOPES.iter().map(|s| s.to_string()).collect());
+    };
+    if let Some(unknown) = requested.iter().find(|s| !SCOPES.contains(&s.trim())) {
+        anyhow::bail!(
+            "unknown scope '{}' (use {})",
+            unknown.trim(),
+            SCOPES.join(", ")
+        );
+    }
+    let has = |scope: &str| requested.iter().any(|s| s.trim() == scope);
+    if !has(SCOPE_READ) && !has(SCOPE_WRITE) {
+        anyhow::bail!("at least the '{SCOPE_READ}' scope is required");
+    }
+    Ok(SCOPES
+        .iter()
+        .filter(|&&scope| has(scope) || (scope == SCOPE_READ && has(SCOPE_WRITE)))
+        .map(|s| s.to_string())
+        .collect())
+}
+
 /// Hash a raw API key for storage/lookup.
 pub fn hash_api_key(raw: &str) -> String {
     let mut hasher = Sha256::new();
@@ -65,3 +103,37 @@ pub fn parse_timestamp(value: &str) -> Option<chrono::DateTime<chrono::Utc>> {
 pub fn is_api_key(raw: &str) -> bool {
     raw.starts_with(API_KEY_PREFIX)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scopes(values: &[&str]) -> Vec<String> {
+        values.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn scopes_are_validated_and_ordered() {
+        assert_eq!(normalize_scopes(None).unwrap(), scopes(DEFAULT_SCOPES));
+        assert_eq!(
+            normalize_scopes(Some(&scopes(&["admin", "write"]))).unwrap(),
+            scopes(&["read", "write", "admin"])
+        );
+        assert_eq!(
+            normalize_scopes(Some(&scopes(&["read", "read"]))).unwrap(),
+            scopes(&["read"])
+        );
+        assert!(normalize_scopes(Some(&scopes(&["delete"]))).is_err());
+        assert!(normalize_scopes(Some(&scopes(&["admin"]))).is_err());
+        assert!(normalize_scopes(Some(&[])).is_err());
+    }
+
+    #[test]
+    fn generated_keys_are_recognized_and_hashed() {
+        let (key, prefix) = generate_api_key();
+        assert!(is_api_key(&key));
+        assert!(key[API_KEY_PREFIX.len()..].starts_with(&prefix));
+        assert_eq!(hash_api_key(&key).len(), 64);
+        assert_ne!(hash_api_key(&key), key);
+    }
+}
@@ -34,5 +34,7 @@ pub struct ApiKeyAuthUser {
     pub email: String,
     pub display_name: String,
     pub role: String,
+    /// Empty for keys created before scopes were enforced.
+    pub scopes: Vec<String>,
     pub expires_at: Option<String>,
 }
@@ -1,8 +1,9 @@
 use anyhow::{Context, Result};
 use serde_json::Value;
-use sqlx::{FromRow, SqlitePool};
+use sqlx::FromRow;
 
 use super::{ApiKeyAuthUser, ApiKeyListItem, parse_timestamp};
+use crate::db::{self, DbPool, on_pool};
 
 #[allow(dead_code)] // Fields populated by sqlx FromRow derive
 #[derive(Debug, Clone, FromRow)]
@@ -39,24 +40,24 @@ struct ApiKeyAuthRow {
 
 #[derive(Debug, Clone)]
 pub struct ApiKeyRepository {
-    pool: SqlitePool,
+    pool: DbPool,
 }
 
 pub type ApiKeyStoreResult<T> = Result<T>;
 
 impl ApiKeyRepository {
-    pub fn new(pool: SqlitePool) -> Self {
+    pub fn new(pool: DbPool) -> Self {
         Self { pool }
     }
 
     pub async fn list_for_user(&self, user_id: &str) -> ApiKeyStoreResult<Vec<ApiKeyListItem>> {
-        let rows = sqlx::query_as::<_, ApiKeyRow>(
+        let rows = on_pool!(&self.pool, |pool| sqlx::query_as::<_, ApiKeyRow>(
             r#"SELECT id, user_id, name, key_prefix, key_hash, scopes, last_used_at, expires_at, created_at, revoked_at
-               FROM api_keys WHERE user_id = ? ORDER BY created_at DESC"#,
+               FROM api_keys WHERE user_id = $1 ORDER BY created_at DESC"#,
         )
         .bind(user_id)
-        .fetch_all(&self.pool)
-        .await
+        .fetch_all(pool)
+        .await)
         .context("list api keys")?;
 
         Ok(rows.into_iter().map(map_row_to_list_item).collect())
@@ -74,9 +75,9 @@ impl ApiKeyRepository {
         let id = uuid::Uuid::new_v4().to_string();
         let scopes_json = serde_json::to_string(&scopes).unwrap_or_else(|_| "[]".to_string());
 
-        sqlx::query(
+        on_pool!(&self.pool, |pool| sqlx::query(
             r#"INSERT INTO api_keys (id, user_id, name, key_prefix, key_hash, scopes, expires_at)
-               VALUES (?, ?, ?, ?, ?, ?, ?)"#,
+               VALUES ($1, $2, $3, $4, $5, $6, $7)"#,
         )
         .bind(&id)
         .bind(user_id)
@@ -85,17 +86,20 @@ impl ApiKeyRepository {
         .bind(key_hash)
         .bind(&scopes_json)
         .bind(&expires_at)
-        .execute(&self.pool)
+        .execute(pool)
         .await
+        .map(|r| r.rows_affected()))
         .context("insert api key")?;
 
-        let row_result = sqlx::query_as::<_, ApiKeyRow>(
+        let row_result = on_pool!(&self.pool, |pool| {
+            sqlx::query_as::<_, ApiKeyRow>(
             r#"SELECT id, user_id, name, key_prefix, key_hash, scopes, last_used_at, expires_at, created_at, revoked_at
-               FROM api_keys WHERE id = ?"#,
+               FROM api_keys WHERE id = $1"#,
         )
         .bind(&id)
-        .fetch_optional(&self.pool)
-        .await;
+        .fetch_optional(pool)
+        .await
+        });
 
         match row_result {
             Ok(Some(row)) => Ok(map_row_to_list_item(row)),
@@ -114,61 +118,89 @@ impl ApiKeyRepository {
         }
     }
 
+    pub async fn get_for_user(
+        &self,
+        user_id: &str,
+        key_id: &str,
+    ) -> ApiKeyStoreResult<Option<ApiKeyListItem>> {
+        let row = on_pool!(&self.pool, |pool| sqlx::query_as::<_, ApiKeyRow>(
+            r#"SELECT id, user_id, name, key_prefix, key_hash, scopes, last_used_at, expires_at, created_at, revoked_at
+               FROM api_keys WHERE id = $1 AND user_id = $2"#,
+        )
+        .bind(key_id)
+        .bind(user_id)
+        .fetch_optional(pool)
+        .await)
+        .context("get api key")?;
+
+        Ok(row.map(map_row_to_list_item))
+    }
+
     pub async fn revoke_by_name(&self, user_id: &str, name: &str) -> ApiKeyStoreResult<u64> {
-        let result = sqlx::query(
-            r#"UPDATE api_keys SET revoked_at = datetime('now')
-               WHERE user_id = ? AND name = ? AND revoked_at IS NULL"#,
+        let result = on_pool!(&self.pool, |pool| sqlx::query(
+            r#"UPDATE api_keys SET revoked_at = $1
+               WHERE user_id = $2 AND name = $3 AND revoked_at IS NULL"#,
         )
+        .bind(db::now())
         .bind(user_id)
         .bind(name)
-        .execute(&self.pool)
+        .execute(pool)
         .await
+        .map(|r| r.rows_affected()))
         .context("revoke api keys by name")?;
 
-        Ok(result.rows_affected())
+        Ok(result)
     }
 
     pub async fn revoke_key(&self, user_id: &str, key_id: &str) -> ApiKeyStoreResult<bool> {
-        let result = sqlx::query(
-            r#"UPDATE api_keys SET revoked_at = datetime('now')
-               WHERE id = ? AND user_id = ? AND revoked_at IS NULL"#,
+        let result = on_pool!(&self.pool, |pool| sqlx::query(
+            r#"UPDATE api_keys SET revoked_at = $1
+               WHERE id = $2 AND user_id = $3 AND revoked_at IS NULL"#,
         )
+        .bind(db::now())
         .bind(key_id)
         .bind(user_id)
-        .execute(&self.pool)
+        .execute(pool)
         .await
+        .map(|r| r.rows_affected()))
         .context("revoke api key")?;
 
-        Ok(result.rows_affected() > 0)
+        Ok(result > 0)
     }
 
     pub async fn delete_key(&self, user_id: &str, key_id: &str) -> ApiKeyStoreResult<bool> {
-        let result = sqlx::query("DELETE FROM api_keys WHERE id = ? AND user_id = ?")
-            .bind(key_id)
-            .bind(user_id)
-            .execute(&self.pool)
-            .await
-            .context("delete api key")?;
-
-        Ok(result.rows_affected() > 0)
+        let result = on_pool!(&self.pool, |pool| sqlx::query(
+            "DELETE FROM api_keys WHERE id = $1 AND user_id = $2"
+        )
+        .bind(key_id)
+        .bind(user_id)
+        .execute(pool)
+        .await
+        .map(|r| r.rows_affected()))
+        .context("delete api key")?;
+
+        Ok(result > 0)
     }
 
     pub async fn find_auth_user_by_hash(
         &self,
         key_hash: &str,
     ) -> ApiKeyStoreResult<Option<ApiKeyAuthUser>> {
-        let row = sqlx::query_as::<_, ApiKeyAuthRow>(
+        let row =
+            on_pool!(&self.pool, |pool| {
+                sqlx::query_as::<_, ApiKeyAuthRow>(
             r#"SELECT k.id, k.user_id, k.name, k.key_prefix, k.key_hash, k.scopes, k.last_used_at,
                       k.expires_at, k.created_at, k.revoked_at, u.email, u.display_name, u.role
                FROM api_keys k
                JOIN users u ON u.id = k.user_id
-               WHERE k.key_hash = ? AND k.revoked_at IS NULL
+               WHERE k.key_hash = $1 AND k.revoked_at IS NULL
                LIMIT 1"#,
         )
         .bind(key_hash)
-        .fetch_optional(&self.pool)
+        .fetch_optional(pool)
         .await
-        .context("get api key auth user")?;
+            })
+            .context("get api key auth user")?;
 
         let Some(row) = row else {
             return Ok(None);
@@ -187,16 +219,21 @@ impl ApiKeyRepository {
             email: row.email,
             display_name: row.display_name,
             role: row.role,
+            scopes: parse_scopes(&row.scopes),
             expires_at: row.expires_at,
         }))
     }
 
     pub async fn touch_last_used(&self, key_id: &str) -> ApiKeyStoreResult<()> {
-        sqlx::query("UPDATE api_keys SET last_used_at = datetime('now') WHERE id = ?")
-            .bind(key_id)
-            .execute(&self.pool)
-            .await
-            .context("touch api key last_used_at")?;
+        on_pool!(&self.pool, |pool| sqlx::query(
+            "UPDATE api_keys SET last_used_at = $1 WHERE id = $2"
+        )
+        .bind(db::now())
+        .bind(key_id)
+        .execute(pool)
+        .await
+        .map(|r| r.rows_affected()))
+        .context("touch api key last_used_at")?;
         Ok(())
     }
 }
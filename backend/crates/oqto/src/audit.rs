@@ -1,15 +1,28 @@
 //! Audit logging for user-facing backend events.
+//!
+//! Events are appended to a JSONL file and, when SIEM export is configured,
+//! queued for the exporter as well. The file is also the store admins query
+//! (`GET /api/admin/audit`); with a retention period set, older events are
+//! pruned from it.
 
 use anyhow::{Context, Result};
-use chrono::{SecondsFormat, Utc};
-use serde::Serialize;
+use chrono::{DateTime, SecondsFormat, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
 use std::path::{Path, PathBuf};
 use std::sync::Arc;
+use std::time::Duration;
 use tokio::fs::{File, OpenOptions};
-use tokio::io::AsyncWriteExt;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
 use tokio::sync::Mutex;
+use tracing::{info, warn};
 
-#[derive(Debug, Serialize)]
+use crate::auth::Actor;
+use crate::impersonation::Impersonation;
+use crate::outbox::OutboxMessage;
+use crate::siem::SiemExporter;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct AuditEvent {
     pub timestamp: String,
     pub event: String,
@@ -29,12 +42,123 @@ pub struct AuditEvent {
     pub session_id: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub workspace_path: Option<String>,
+    /// Login name given in authentication events.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub username: Option<String>,
+    /// Why an authentication attempt failed.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+    /// Peer address of the request.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub client_ip: Option<String>,
+    /// `X-Forwarded-For` as received.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub forwarded_for: Option<String>,
+    /// Outbox message of an outbound message event.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub outbox_id: Option<String>,
+    /// Outbound channel (`email`, `slack`).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub channel: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub recipients: Option<Vec<String>>,
+    /// Admin who acted as `user_id`: set on everything done while
+    /// impersonating, and on the impersonation's own events.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub impersonated_by: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub impersonation_id: Option<String>,
+}
+
+/// How often events past the retention period are pruned.
+const RETENTION_INTERVAL: Duration = Duration::from_secs(6 * 3600);
+
+/// Filters for [`AuditLogger::query`]. Unset fields match everything.
+#[derive(Debug, Clone, Default)]
+pub struct AuditQuery {
+    pub user_id: Option<String>,
+    /// Event type, e.g. `http_request`, `ws_command` or `auth_login_failed`.
+    pub event: Option<String>,
+    pub session_id: Option<String>,
+    pub impersonation_id: Option<String>,
+    /// Events at or after this time.
+    pub since: Option<DateTime<Utc>>,
+    /// Events before this time.
+    pub until: Option<DateTime<Utc>>,
+    pub limit: usize,
+    /// Matching events to skip, newest first.
+    pub offset: usize,
+}
+
+/// A page of matching events, newest first.
+#[derive(Debug, Serialize)]
+pub struct AuditPage {
+    pub events: Vec<AuditEvent>,
+    /// Matching events in total.
+    pub total: usize,
+    pub limit: usize,
+    pub offset: usize,
+}
+
+/// Columns of [`events_to_csv`], in order.
+pub const CSV_COLUMNS: &[&str] = &[
+    "timestamp",
+    "event",
+    "user_id",
+    "method",
+    "path",
+    "status",
+    "duration_ms",
+    "ws_command",
+    "session_id",
+    "workspace_path",
+    "username",
+    "reason",
+    "client_ip",
+    "forwarded_for",
+    "outbox_id",
+    "channel",
+    "recipients",
+    "impersonated_by",
+    "impersonation_id",
+];
+
+/// Timestamps are written in this format, so they compare as strings.
+fn format_timestamp(time: DateTime<Utc>) -> String {
+    time.to_rfc3339_opts(SecondsFormat::Millis, true)
+}
+
+impl AuditQuery {
+    fn matches(&self, event: &AuditEvent, since: Option<&str>, until: Option<&str>) -> bool {
+        let field = |filter: &Option<String>, value: &Option<String>| {
+            filter
+                .as_deref()
+                .is_none_or(|filter| value.as_deref() == Some(filter))
+        };
+        (self.event.as_deref().is_none_or(|e| event.event == e))
+            && field(&self.user_id, &event.user_id)
+            && field(&self.session_id, &event.session_id)
+            && field(&self.impersonation_id, &event.impersonation_id)
+            && since.is_none_or(|since| event.timestamp.as_str() >= since)
+            && until.is_none_or(|until| event.timestamp.as_str() < until)
+    }
+}
+
+/// Authentication event details for [`AuditLogger::log_auth`].
+#[derive(Debug, Default)]
+pub struct AuthAudit<'a> {
+    pub user_id: Option<&'a str>,
+    pub username: Option<&'a str>,
+    pub reason: Option<&'a str>,
+    pub client_ip: Option<String>,
+    pub forwarded_for: Option<&'a str>,
 }
 
 #[derive(Clone)]
 pub struct AuditLogger {
     file: Arc<Mutex<File>>,
     path: PathBuf,
+    exporter: Option<Arc<SiemExporter>>,
 }
 
 impl AuditLogger {
@@ -49,9 +173,16 @@ impl AuditLogger {
         Ok(Self {
             file: Arc::new(Mutex::new(file)),
             path,
+            exporter: None,
         })
     }
 
+    /// Also stream events to a SIEM.
+    pub fn with_exporter(mut self, exporter: Arc<SiemExporter>) -> Self {
+        self.exporter = Some(exporter);
+        self
+    }
+
     pub fn path(&self) -> &Path {
         &self.path
     }
@@ -59,6 +190,7 @@ impl AuditLogger {
     pub async fn log_http(
         &self,
         user_id: &str,
+        impersonation: Option<&Actor>,
         method: &str,
         path: &str,
         status: u16,
@@ -75,6 +207,15 @@ impl AuditLogger {
             ws_command: None,
             session_id: None,
             workspace_path: None,
+            username: None,
+            reason: None,
+            client_ip: None,
+            forwarded_for: None,
+            outbox_id: None,
+            channel: None,
+            recipients: None,
+            impersonated_by: impersonation.map(|actor| actor.sub.clone()),
+            impersonation_id: impersonation.map(|actor| actor.impersonation_id.clone()),
         };
         self.write_event(&event).await;
     }
@@ -82,6 +223,7 @@ impl AuditLogger {
     pub async fn log_ws_command(
         &self,
         user_id: &str,
+        impersonation: Option<&Actor>,
         command: &str,
         session_id: Option<&str>,
         workspace_path: Option<&str>,
@@ -97,11 +239,233 @@ impl AuditLogger {
             ws_command: Some(command.to_string()),
             session_id: session_id.map(|s| s.to_string()),
             workspace_path: workspace_path.map(|s| s.to_string()),
+            username: None,
+            reason: None,
+            client_ip: None,
+            forwarded_for: None,
+            outbox_id: None,
+            channel: None,
+            recipients: None,
+            impersonated_by: impersonation.map(|actor| actor.sub.clone()),
+            impersonation_id: impersonation.map(|actor| actor.impersonation_id.clone()),
         };
         self.write_event(&event).await;
     }
 
+    /// Record an authentication event (`auth_login`, `auth_login_failed`, ...).
+    pub async fn log_auth(&self, event: &str, details: AuthAudit<'_>) {
+        let event = AuditEvent {
+            timestamp: Utc::now().to_rfc3339_opts(SecondsFormat::Millis, true),
+            event: event.to_string(),
+            user_id: details.user_id.map(|s| s.to_string()),
+            method: None,
+            path: None,
+            status: None,
+            duration_ms: None,
+            ws_command: None,
+            session_id: None,
+            workspace_path: None,
+            username: details.username.map(|s| s.to_string()),
+            reason: details.reason.map(|s| s.to_string()),
+            client_ip: details.client_ip,
+            forwarded_for: details.forwarded_for.map(|s| s.to_string()),
+            outbox_id: None,
+            channel: None,
+            recipients: None,
+            impersonated_by: None,
+            impersonation_id: None,
+        };
+        self.write_event(&event).await;
+    }
+
+    /// Record a step of an outbound message (`outbox_staged`,
+    /// `outbox_approved`, `outbox_sent`, ...). `user_id` is who acted.
+    pub async fn log_outbox(
+        &self,
+        event: &str,
+        user_id: &str,
+        message: &OutboxMessage,
+        reason: Option<&str>,
+    ) {
+        let event = AuditEvent {
+            timestamp: Utc::now().to_rfc3339_opts(SecondsFormat::Millis, true),
+            event: event.to_string(),
+            user_id: Some(user_id.to_string()),
+            method: None,
+            path: None,
+            status: None,
+            duration_ms: None,
+            ws_command: None,
+            session_id: Some(message.session_id.clone()),
+            workspace_path: None,
+            username: None,
+            reason: reason.map(|s| s.to_string()),
+            client_ip: None,
+            forwarded_for: None,
+            outbox_id: Some(message.id.clone()),
+            channel: Some(message.channel.to_string()),
+            recipients: Some(
+                message
+                    .content
+                    .recipients
+                    .iter()
+                    .chain(&message.content.cc)
+                    .cloned()
+                    .collect(),
+            ),
+            impersonated_by: None,
+            impersonation_id: None,
+        };
+        self.write_event(&event).await;
+    }
+
+    /// Record a step of an impersonation (`impersonation_requested`,
+    /// `impersonation_approved`, `impersonation_ended`, ...). The event
+    /// belongs to the impersonated user and carries the admin's reason.
+    pub async fn log_impersonation(&self, event: &str, impersonation: &Impersonation) {
+        let event = AuditEvent {
+            timestamp: Utc::now().to_rfc3339_opts(SecondsFormat::Millis, true),
+            event: event.to_string(),
+            user_id: Some(impersonation.user_id.clone()),
+            method: None,
+            path: None,
+            status: None,
+            duration_ms: None,
+            ws_command: None,
+            session_id: None,
+            workspace_path: None,
+            username: None,
+            reason: Some(impersonation.reason.clone()),
+            client_ip: None,
+            forwarded_for: None,
+            outbox_id: None,
+            channel: None,
+            recipients: None,
+            impersonated_by: Some(impersonation.admin_id.clone()),
+            impersonation_id: Some(impersonation.id.clone()),
+        };
+        self.write_event(&event).await;
+    }
+
+    /// Read matching events from the log file.
+    pub async fn query(&self, query: &AuditQuery) -> Result<AuditPage> {
+        let since = query.since.map(format_timestamp);
+        let until = query.until.map(format_timestamp);
+        let file = File::open(&self.path)
+            .await
+            .with_context(|| format!("opening audit log file {}", self.path.display()))?;
+        let mut lines = BufReader::new(file).lines();
+
+        // The file is oldest first; keep only the newest offset + limit.
+        let keep = query.offset + query.limit;
+        let mut newest = VecDeque::with_capacity(keep.min(1024));
+        let mut total = 0;
+        while let Some(line) = lines.next_line().await.context("reading audit log")? {
+            let Ok(event) = serde_json::from_str::<AuditEvent>(&line) else {
+                continue;
+            };
+            if !query.matches(&event, since.as_deref(), until.as_deref()) {
+                continue;
+            }
+            total += 1;
+            if keep == 0 {
+                continue;
+            }
+            if newest.len() == keep {
+                newest.pop_front();
+            }
+            newest.push_back(event);
+        }
+
+        Ok(AuditPage {
+            events: newest
+                .into_iter()
+                .rev()
+                .skip(query.offset)
+                .take(query.limit)
+                .collect(),
+            total,
+            limit: query.limit,
+            offset: query.offset,
+        })
+    }
+
+    /// Drop events older than `retention` from the log file. Returns how
+    /// many were removed.
+    pub async fn prune(&self, retention: Duration) -> Result<usize> {
+        let cutoff = chrono::Duration::from_std(retention)
+            .ok()
+            .and_then(|retention| Utc::now().checked_sub_signed(retention))
+            .map(format_timestamp)
+            .unwrap_or_default();
+        let tmp_path = self.path.with_extension("jsonl.prune");
+
+        // Writers wait while the file is rewritten.
+        let mut file = self.file.lock().await;
+        file.flush().await.context("flushing audit log")?;
+
+        let source = File::open(&self.path)
+            .await
+            .with_context(|| format!("opening audit log file {}", self.path.display()))?;
+        let mut lines = BufReader::new(source).lines();
+        let mut kept = File::create(&tmp_path)
+            .await
+            .with_context(|| format!("creating {}", tmp_path.display()))?;
+        let mut removed = 0;
+        while let Some(line) = lines.next_line().await.context("reading audit log")? {
+            // Lines that cannot be dated are kept.
+            let expired = serde_json::from_str::<AuditEvent>(&line)
+                .is_ok_and(|event| event.timestamp < cutoff);
+            if expired {
+                removed += 1;
+                continue;
+            }
+            kept.write_all(line.as_bytes()).await?;
+            kept.write_all(b"\n").await?;
+        }
+        kept.flush().await?;
+        drop(kept);
+
+        if removed == 0 {
+            let _ = tokio::fs::remove_file(&tmp_path).await;
+            return Ok(0);
+        }
+        tokio::fs::rename(&tmp_path, &self.path)
+            .await
+            .with_context(|| format!("replacing audit log file {}", self.path.display()))?;
+        *file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+            .await
+            .with_context(|| format!("reopening audit log file {}", self.path.display()))?;
+        Ok(removed)
+    }
+
+    /// Prune events older than `retention_days` now and periodically.
+    pub fn start_retention_task(
+        self: &Arc<Self>,
+        retention_days: u64,
+    ) -> tokio::task::JoinHandle<()> {
+        let logger = Arc::clone(self);
+        let retention = Duration::from_secs(retention_days.saturating_mul(24 * 3600));
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(RETENTION_INTERVAL);
+            loop {
+                interval.tick().await;
+                match logger.prune(retention).await {
+                    Ok(0) => {}
+                    Ok(n) => info!("Pruned {n} audit events older than {retention_days} days"),
+                    Err(e) => warn!("Failed to prune audit log: {e:#}"),
+                }
+            }
+        })
+    }
+
     async fn write_event(&self, event: &AuditEvent) {
+        if let Some(exporter) = &self.exporter {
+            exporter.push(event);
+        }
         if let Ok(line) = serde_json::to_string(event) {
             let mut file = self.file.lock().await;
             if file.write_all(line.as_bytes()).await.is_ok() {
@@ -111,6 +475,47 @@ impl AuditLogger {
     }
 }
 
+/// Render events as CSV with a header row. Recipients are joined with `;`.
+pub fn events_to_csv(events: &[AuditEvent]) -> String {
+    let mut out = String::new();
+    crate::invite::csv::write_record(&mut out, CSV_COLUMNS.iter().copied());
+    for event in events {
+        let status = event.status.map(|s| s.to_string()).unwrap_or_default();
+        let duration = event.duration_ms.map(|d| d.to_string()).unwrap_or_default();
+        let recipients = event
+            .recipients
+            .as_ref()
+            .map(|r| r.join(";"))
+            .unwrap_or_default();
+        let text = |value: &Option<String>| value.as_deref().unwrap_or_default();
+        crate::invite::csv::write_record(
+            &mut out,
+            [
+                event.timestamp.as_str(),
+                &event.event,
+                text(&event.user_id),
+                text(&event.method),
+                text(&event.path),
+                &status,
+                &duration,
+                text(&event.ws_command),
+                text(&event.session_id),
+                text(&event.workspace_path),
+                text(&event.username),
+                text(&event.reason),
+                text(&event.client_ip),
+                text(&event.forwarded_for),
+                text(&event.outbox_id),
+                text(&event.channel),
+                &recipients,
+                text(&event.impersonated_by),
+                text(&event.impersonation_id),
+            ],
+        );
+    }
+    out
+}
+
 fn ensure_parent_dir(path: &Path) -> Result<()> {
     if let Some(parent) = path.parent() {
         std::fs::create_dir_all(parent)
@@ -118,3 +523,138 @@ fn ensure_parent_dir(path: &Path) -> Result<()> {
     }
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn logger() -> (tempfile::TempDir, AuditLogger) {
+        let dir = tempfile::tempdir().unwrap();
+        let logger = AuditLogger::new(dir.path().join("audit.log.jsonl"))
+            .await
+            .unwrap();
+        (dir, logger)
+    }
+
+    #[tokio::test]
+    async fn test_query_filters_and_pages() {
+        let (_dir, logger) = logger().await;
+        logger
+            .log_http("alice", None, "GET", "/api/sessions", 200, 3)
+            .await;
+        logger
+            .log_ws_command("alice", None, "agent.prompt", Some("ses_1"), None)
+            .await;
+        logger
+            .log_ws_command("bob", None, "agent.prompt", Some("ses_2"), None)
+            .await;
+        logger
+            .log_ws_command("alice", None, "agent.abort", Some("ses_1"), None)
+            .await;
+
+        let page = logger
+            .query(&AuditQuery {
+                user_id: Some("alice".to_string()),
+                event: Some("ws_command".to_string()),
+                limit: 1,
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+        assert_eq!(page.total, 2);
+        assert_eq!(page.events.len(), 1);
+        // Newest first.
+        assert_eq!(page.events[0].ws_command.as_deref(), Some("agent.abort"));
+
+        let page = logger
+            .query(&AuditQuery {
+                session_id: Some("ses_1".to_string()),
+                limit: 10,
+                offset: 1,
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+        assert_eq!(page.total, 2);
+        assert_eq!(page.events[0].ws_command.as_deref(), Some("agent.prompt"));
+
+        let page = logger
+            .query(&AuditQuery {
+                since: Some(Utc::now() + chrono::Duration::hours(1)),
+                limit: 10,
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+        assert_eq!(page.total, 0);
+
+        let csv = events_to_csv(&page.events);
+        assert!(csv.starts_with("timestamp,event,user_id,"));
+    }
+
+    #[tokio::test]
+    async fn test_impersonated_events_are_watermarked() {
+        let (_dir, logger) = logger().await;
+        let actor = Actor {
+            sub: "admin".to_string(),
+            impersonation_id: "imp_1".to_string(),
+        };
+        logger
+            .log_http("alice", Some(&actor), "GET", "/api/sessions", 200, 3)
+            .await;
+        logger
+            .log_ws_command("alice", Some(&actor), "agent.prompt", Some("ses_1"), None)
+            .await;
+        logger
+            .log_http("alice", None, "GET", "/api/sessions", 200, 2)
+            .await;
+
+        let page = logger
+            .query(&AuditQuery {
+                impersonation_id: Some("imp_1".to_string()),
+                limit: 10,
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+        assert_eq!(page.total, 2);
+        assert!(
+            page.events
+                .iter()
+                .all(|e| e.impersonated_by.as_deref() == Some("admin"))
+        );
+        let csv = events_to_csv(&page.events);
+        assert!(csv.lines().nth(1).unwrap().ends_with(",admin,imp_1"));
+    }
+
+    #[tokio::test]
+    async fn test_prune_keeps_recent_events() {
+        let (_dir, logger) = logger().await;
+        let old: AuditEvent = serde_json::from_str(
+            r#"{"timestamp":"2020-01-01T00:00:00.000Z","event":"http_request"}"#,
+        )
+        .unwrap();
+        logger.write_event(&old).await;
+        logger
+            .log_http("alice", None, "GET", "/api/me", 200, 1)
+            .await;
+
+        assert_eq!(
+            logger
+                .prune(Duration::from_secs(30 * 24 * 3600))
+                .await
+                .unwrap(),
+            1
+        );
+        // Writes after the rewrite land in the new file.
+        logger.log_http("bob", None, "GET", "/api/me", 200, 1).await;
+        let page = logger
+            .query(&AuditQuery {
+                limit: 10,
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+        assert_eq!(page.total, 2);
+    }
+}
@@ -84,6 +84,24 @@ pub struct Claims {
     /// Custom role claim (alternative to roles array).
     #[serde(default)]
     pub role: Option<String>,
+
+    /// Admin acting as the user, on tokens issued for an impersonation.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub act: Option<Actor>,
+
+    /// Space-separated scopes limiting what the token may do, on tokens
+    /// backed by a scoped API key. Absent: no limit beyond the user's role.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub scope: Option<String>,
+}
+
+/// Who is acting on a user's behalf (RFC 8693 `act` claim).
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Actor {
+    /// Admin user ID.
+    pub sub: String,
+    /// Impersonation the token was issued for.
+    pub impersonation_id: String,
 }
 
 impl Claims {
@@ -111,6 +129,14 @@ impl Claims {
         self.effective_role() == Role::Admin
     }
 
+    /// Whether the token carries `scope`; tokens without a `scope` claim
+    /// carry every scope.
+    pub fn has_scope(&self, scope: &str) -> bool {
+        self.scope
+            .as_deref()
+            .is_none_or(|scopes| scopes.split_whitespace().any(|s| s == scope))
+    }
+
     /// Get the display name for the user.
     pub fn display_name(&self) -> &str {
         self.name
@@ -154,6 +180,8 @@ mod tests {
             preferred_username: None,
             roles: vec![],
             role: None,
+            act: None,
+            scope: None,
         };
         assert_eq!(claims.effective_role(), Role::User);
 
@@ -185,6 +213,8 @@ mod tests {
             preferred_username: Some("johnd".to_string()),
             roles: vec![],
             role: None,
+            act: None,
+            scope: None,
         };
         assert_eq!(claims.display_name(), "John Doe");
 
@@ -209,4 +239,32 @@ mod tests {
         };
         assert_eq!(claims_only_sub.display_name(), "user123");
     }
+
+    #[test]
+    fn test_claims_scope() {
+        let claims = Claims {
+            sub: "user123".to_string(),
+            iss: Some("api_key".to_string()),
+            aud: None,
+            exp: 0,
+            iat: None,
+            nbf: None,
+            jti: None,
+            email: None,
+            name: None,
+            preferred_username: None,
+            roles: vec![],
+            role: None,
+            act: None,
+            scope: None,
+        };
+        assert!(claims.has_scope("write"));
+
+        let read_only = Claims {
+            scope: Some("read".to_string()),
+            ..claims
+        };
+        assert!(read_only.has_scope("read"));
+        assert!(!read_only.has_scope("write"));
+    }
 }
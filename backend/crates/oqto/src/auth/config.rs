@@ -1,6 +1,8 @@
 //! Authentication configuration.
 
 use super::Role;
+use super::oidc::OidcConfig;
+use super::passkeys::PasskeyConfig;
 use serde::{Deserialize, Serialize};
 
 /// Authentication configuration.
@@ -27,6 +29,12 @@ pub struct AuthConfig {
     /// Allowed CORS origins. If empty in production, CORS is disabled.
     #[serde(default)]
     pub allowed_origins: Vec<String>,
+
+    /// Sign-in through an OpenID Connect provider (`[auth.oidc]`).
+    pub oidc: OidcConfig,
+
+    /// Passwordless sign-in with passkeys (`[auth.passkeys]`).
+    pub passkeys: PasskeyConfig,
 }
 
 impl Default for AuthConfig {
@@ -42,6 +50,8 @@ impl Default for AuthConfig {
                 "http://localhost:3000".to_string(),
                 "http://localhost:8080".to_string(),
             ],
+            oidc: OidcConfig::default(),
+            passkeys: PasskeyConfig::default(),
         }
     }
 }
@@ -50,20 +60,7 @@ impl AuthConfig {
     /// Resolve the JWT secret, expanding `env:VAR_NAME` syntax.
     /// Returns the resolved secret or None if not configured.
     pub fn resolve_jwt_secret(&self) -> Result<Option<String>, ConfigValidationError> {
-        match &self.jwt_secret {
-            None => Ok(None),
-            Some(value) => {
-                if let Some(var_name) = value.strip_prefix("env:") {
-                    match std::env::var(var_name) {
-                        Ok(secret) if !secret.is_empty() => Ok(Some(secret)),
-                        Ok(_) => Err(ConfigValidationError::EnvVarEmpty(var_name.to_string())),
-                        Err(_) => Err(ConfigValidationError::EnvVarNotFound(var_name.to_string())),
-                    }
-                } else {
-                    Ok(Some(value.clone()))
-                }
-            }
-        }
+        self.jwt_secret.as_deref().map(resolve_secret).transpose()
     }
 
     /// Validate the configuration.
@@ -89,6 +86,14 @@ impl AuthConfig {
             }
         }
 
+        if self.oidc.enabled {
+            self.oidc.validate()?;
+        }
+
+        if self.passkeys.enabled {
+            self.passkeys.validate()?;
+        }
+
         Ok(())
     }
 
@@ -113,6 +118,18 @@ impl AuthConfig {
     }
 }
 
+/// Resolve a configured secret, expanding `env:VAR_NAME` syntax.
+pub fn resolve_secret(value: &str) -> Result<String, ConfigValidationError> {
+    let Some(var_name) = value.strip_prefix("env:") else {
+        return Ok(value.to_string());
+    };
+    match std::env::var(var_name) {
+        Ok(secret) if !secret.is_empty() => Ok(secret),
+        Ok(_) => Err(ConfigValidationError::EnvVarEmpty(var_name.to_string())),
+        Err(_) => Err(ConfigValidationError::EnvVarNotFound(var_name.to_string())),
+    }
+}
+
 /// Configuration validation errors.
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum ConfigValidationError {
@@ -126,6 +143,10 @@ pub enum ConfigValidationError {
     EnvVarNotFound(String),
     /// Environment variable is empty (for `env:VAR_NAME` syntax).
     EnvVarEmpty(String),
+    /// A required `[auth.oidc]` setting is missing or invalid.
+    InvalidOidc(String),
+    /// A required `[auth.passkeys]` setting is missing or invalid.
+    InvalidPasskeys(String),
 }
 
 impl std::fmt::Display for ConfigValidationError {
@@ -163,6 +184,10 @@ impl std::fmt::Display for ConfigValidationError {
                     var, var
                 )
             }
+            Self::InvalidOidc(reason) => write!(f, "Invalid [auth.oidc] configuration: {reason}"),
+            Self::InvalidPasskeys(reason) => {
+                write!(f, "Invalid [auth.passkeys] configuration: {reason}")
+            }
         }
     }
 }
@@ -27,6 +27,10 @@ pub enum AuthError {
     #[error("token expired")]
     TokenExpired,
 
+    /// Token was revoked before its expiry.
+    #[error("token revoked")]
+    TokenRevoked,
+
     /// Insufficient permissions.
     #[error("insufficient permissions: {0}")]
     InsufficientPermissions(String),
@@ -58,6 +62,7 @@ impl IntoResponse for AuthError {
             AuthError::InvalidAuthHeader => (StatusCode::UNAUTHORIZED, "invalid_auth_header"),
             AuthError::InvalidToken(_) => (StatusCode::UNAUTHORIZED, "invalid_token"),
             AuthError::TokenExpired => (StatusCode::UNAUTHORIZED, "token_expired"),
+            AuthError::TokenRevoked => (StatusCode::UNAUTHORIZED, "token_revoked"),
             AuthError::InsufficientPermissions(_) => {
                 (StatusCode::FORBIDDEN, "insufficient_permissions")
             }
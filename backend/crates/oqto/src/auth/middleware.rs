@@ -12,9 +12,16 @@ use chrono::Utc;
 use jsonwebtoken::{Algorithm, DecodingKey, Validation, decode};
 use log::{debug, warn};
 
-use crate::api_keys::{ApiKeyRepository, hash_api_key, is_api_key, parse_timestamp};
+use crate::api_keys::{
+    ApiKeyRepository, SCOPE_ADMIN, SCOPE_READ, SCOPE_WRITE, hash_api_key, is_api_key,
+    parse_timestamp,
+};
+use crate::impersonation::ImpersonationRepository;
 
-use super::{AuthConfig, AuthError, Claims, DevUser, Role};
+use super::{
+    Actor, AuthConfig, AuthError, Claims, DevUser, RevocationRegistry, RevocationScope, Role,
+    TokenRevocation,
+};
 
 /// Extract a Bearer token from an Authorization header value.
 fn bearer_token_from_header(header_value: &str) -> Result<&str, AuthError> {
@@ -37,7 +44,10 @@ fn bearer_token_from_header(header_value: &str) -> Result<&str, AuthError> {
     Ok(token)
 }
 
-fn token_from_cookie_header<'a>(cookie_header: &'a str, cookie_name: &str) -> Option<&'a str> {
+pub(super) fn token_from_cookie_header<'a>(
+    cookie_header: &'a str,
+    cookie_name: &str,
+) -> Option<&'a str> {
     cookie_header.split(';').map(str::trim).find_map(|pair| {
         let (name, value) = pair.split_once('=')?;
         if name.trim() == cookie_name {
@@ -53,6 +63,7 @@ fn token_from_cookie_header<'a>(cookie_header: &'a str, cookie_name: &str) -> Op
 pub struct AuthState {
     config: Arc<AuthConfig>,
     decoding_key: Option<DecodingKey>,
+    revocations: Arc<RevocationRegistry>,
 }
 
 /// State passed to the authentication middleware.
@@ -60,6 +71,8 @@ pub struct AuthState {
 pub struct AuthMiddlewareState {
     pub auth: AuthState,
     pub api_keys: Option<ApiKeyRepository>,
+    /// Checks that impersonation tokens are still good.
+    pub impersonations: Option<ImpersonationRepository>,
 }
 
 impl AuthState {
@@ -87,6 +100,7 @@ impl AuthState {
         Self {
             config: Arc::new(config),
             decoding_key,
+            revocations: Arc::new(RevocationRegistry::new()),
         }
     }
 
@@ -148,9 +162,35 @@ impl AuthState {
             }
         })?;
 
+        if self.revocations.is_revoked(&token_data.claims) {
+            return Err(AuthError::TokenRevoked);
+        }
+
         Ok(token_data.claims)
     }
 
+    /// Revoke a user's credentials and notify live connections.
+    ///
+    /// With [`RevocationScope::AllTokens`], JWTs issued up to now are rejected
+    /// from here on. API keys and impersonations are revoked in the
+    /// database; the [`RevocationScope::ApiKeys`] and
+    /// [`RevocationScope::Impersonation`] notices only tear down open
+    /// connections.
+    pub fn revoke_user_tokens(&self, user_id: &str, scope: RevocationScope, reason: &str) {
+        let revocation = self.revocations.revoke(user_id, scope, reason);
+        tracing::info!(
+            user_id = %revocation.user_id,
+            scope = ?revocation.scope,
+            reason = %revocation.reason,
+            "Revoked user tokens"
+        );
+    }
+
+    /// Subscribe to token revocation notices.
+    pub fn subscribe_revocations(&self) -> tokio::sync::broadcast::Receiver<TokenRevocation> {
+        self.revocations.subscribe()
+    }
+
     /// Get claims for a dev user.
     fn get_dev_user_claims(&self, user_id: &str) -> Result<Claims, AuthError> {
         let user = self
@@ -173,6 +213,8 @@ impl AuthState {
             preferred_username: Some(user.id.clone()),
             roles: vec![user.role.to_string()],
             role: Some(user.role.to_string()),
+            act: None,
+            scope: None,
         })
     }
 
@@ -189,14 +231,6 @@ impl AuthState {
         name: &str,
         role: &str,
     ) -> Result<String, AuthError> {
-        use jsonwebtoken::{EncodingKey, Header, encode};
-
-        let secret = self
-            .config
-            .jwt_secret
-            .as_ref()
-            .ok_or_else(|| AuthError::Internal("no JWT secret configured".to_string()))?;
-
         let claims = Claims {
             sub: user_id.to_string(),
             iss: Some("workspace-backend".to_string()),
@@ -210,11 +244,55 @@ impl AuthState {
             preferred_username: Some(user_id.to_string()),
             roles: vec![role.to_string()],
             role: Some(role.to_string()),
+            act: None,
+            scope: None,
         };
+        self.sign(&claims)
+    }
+
+    /// Generate a token that lets an admin act as a user until `expires_at`
+    /// (Unix timestamp). The user's own claims are kept; `act` names the
+    /// admin and the impersonation.
+    pub fn generate_impersonation_token(
+        &self,
+        user_id: &str,
+        email: &str,
+        name: &str,
+        role: &str,
+        actor: Actor,
+        expires_at: i64,
+    ) -> Result<String, AuthError> {
+        let claims = Claims {
+            sub: user_id.to_string(),
+            iss: Some("workspace-backend".to_string()),
+            aud: None,
+            exp: expires_at,
+            iat: Some(Utc::now().timestamp()),
+            nbf: None,
+            jti: None,
+            email: Some(email.to_string()),
+            name: Some(name.to_string()),
+            preferred_username: Some(user_id.to_string()),
+            roles: vec![role.to_string()],
+            role: Some(role.to_string()),
+            act: Some(actor),
+            scope: None,
+        };
+        self.sign(&claims)
+    }
+
+    fn sign(&self, claims: &Claims) -> Result<String, AuthError> {
+        use jsonwebtoken::{EncodingKey, Header, encode};
+
+        let secret = self
+            .config
+            .jwt_secret
+            .as_ref()
+            .ok_or_else(|| AuthError::Internal("no JWT secret configured".to_string()))?;
 
         encode(
             &Header::default(),
-            &claims,
+            claims,
             &EncodingKey::from_secret(secret.as_bytes()),
         )
         .map_err(|e| AuthError::Internal(e.to_string()))
@@ -248,6 +326,11 @@ impl CurrentUser {
     pub fn display_name(&self) -> &str {
         self.claims.display_name()
     }
+
+    /// The admin acting as this user, if the request is impersonated.
+    pub fn impersonator(&self) -> Option<&Actor> {
+        self.claims.act.as_ref()
+    }
 }
 
 /// Extract authentication from request.
@@ -365,6 +448,17 @@ pub async fn auth_middleware(
         return Err(AuthError::MissingAuthHeader);
     };
 
+    if claims.act.is_some() {
+        check_impersonation(state.impersonations.as_ref(), &claims).await?;
+    }
+
+    let scope = required_scope(&req);
+    if !claims.has_scope(scope) {
+        return Err(AuthError::InsufficientPermissions(format!(
+            "token lacks the '{scope}' scope"
+        )));
+    }
+
     // Inject current user into extensions
     let user = CurrentUser { claims };
     req.extensions_mut().insert(user);
@@ -380,6 +474,13 @@ async fn api_key_to_claims(
         AuthError::InvalidToken("api key authentication not configured".to_string())
     })?;
 
+    api_key_claims(repo, raw_key).await
+}
+
+/// Resolve an API key to claims.
+///
+/// Shared by the HTTP middleware and WebSocket token refresh.
+pub async fn api_key_claims(repo: &ApiKeyRepository, raw_key: &str) -> Result<Claims, AuthError> {
     let key_hash = hash_api_key(raw_key);
     let auth_user = repo
         .find_auth_user_by_hash(&key_hash)
@@ -403,7 +504,12 @@ async fn api_key_to_claims(
         warn!("Failed to update api key last_used_at: {}", err);
     }
 
-    let role = auth_user.role.parse::<Role>().unwrap_or(Role::User);
+    // Keys from before scopes were enforced carry none and stay unrestricted.
+    let scope = (!auth_user.scopes.is_empty()).then(|| auth_user.scopes.join(" "));
+    let mut role = auth_user.role.parse::<Role>().unwrap_or(Role::User);
+    if scope.is_some() && !auth_user.scopes.iter().any(|s| s == SCOPE_ADMIN) {
+        role = Role::User;
+    }
 
     Ok(Claims {
         sub: auth_user.user_id.clone(),
@@ -418,9 +524,50 @@ async fn api_key_to_claims(
         preferred_username: Some(auth_user.user_id.clone()),
         roles: vec![role.to_string()],
         role: Some(role.to_string()),
+        act: None,
+        scope,
     })
 }
 
+/// Scope a request needs from a scoped token: `read` for safe methods,
+/// `write` for anything that can change state. WebSockets carry commands,
+/// so opening one needs `write` too.
+fn required_scope(req: &axum::http::Request<axum::body::Body>) -> &'static str {
+    let safe = matches!(
+        *req.method(),
+        axum::http::Method::GET | axum::http::Method::HEAD | axum::http::Method::OPTIONS
+    );
+    let upgrade = req.headers().contains_key(header::UPGRADE);
+    if safe && !upgrade {
+        SCOPE_READ
+    } else {
+        SCOPE_WRITE
+    }
+}
+
+/// Reject impersonation tokens whose impersonation was ended early; expiry
+/// is covered by `exp`.
+///
+/// Shared by the HTTP middleware and WebSocket token refresh.
+pub async fn check_impersonation(
+    repo: Option<&ImpersonationRepository>,
+    claims: &Claims,
+) -> Result<(), AuthError> {
+    let Some(actor) = claims.act.as_ref() else {
+        return Ok(());
+    };
+    let repo = repo.ok_or(AuthError::TokenRevoked)?;
+    let active = repo
+        .is_active(&actor.impersonation_id, &claims.sub)
+        .await
+        .map_err(|err| AuthError::Internal(err.to_string()))?;
+    if active {
+        Ok(())
+    } else {
+        Err(AuthError::TokenRevoked)
+    }
+}
+
 /// Check if this is a WebSocket path that supports query parameter authentication.
 ///
 /// WebSocket connections cannot send custom headers after the initial handshake,
@@ -517,6 +664,22 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_required_scope() {
+        let request = |method: &str, upgrade: bool| {
+            let mut builder = axum::http::Request::builder().method(method).uri("/ws/mux");
+            if upgrade {
+                builder = builder.header(header::UPGRADE, "websocket");
+            }
+            builder.body(axum::body::Body::empty()).unwrap()
+        };
+        assert_eq!(required_scope(&request("GET", false)), SCOPE_READ);
+        assert_eq!(required_scope(&request("HEAD", false)), SCOPE_READ);
+        assert_eq!(required_scope(&request("POST", false)), SCOPE_WRITE);
+        assert_eq!(required_scope(&request("DELETE", false)), SCOPE_WRITE);
+        assert_eq!(required_scope(&request("GET", true)), SCOPE_WRITE);
+    }
+
     fn make_dev_user(id: &str, name: &str, email: &str, password: &str, role: Role) -> DevUser {
         let password_hash =
             bcrypt::hash(password, bcrypt::DEFAULT_COST).expect("Failed to hash password");
@@ -627,6 +790,62 @@ mod tests {
         assert!(result.is_err());
     }
 
+    #[test]
+    fn test_revoked_token_is_rejected() {
+        let config = AuthConfig {
+            jwt_secret: Some("test-secret-for-unit-tests-minimum-32-chars-long".to_string()),
+            ..Default::default()
+        };
+        let state = AuthState::new(config);
+
+        let token = state
+            .generate_token("alice", "alice@example.com", "Alice", "user")
+            .unwrap();
+        assert!(state.validate_token(&token).is_ok());
+
+        state.revoke_user_tokens("alice", RevocationScope::AllTokens, "role changed");
+        assert!(matches!(
+            state.validate_token(&token),
+            Err(AuthError::TokenRevoked)
+        ));
+    }
+
+    #[test]
+    fn test_impersonation_token_names_actor() {
+        let config = AuthConfig {
+            jwt_secret: Some("test-secret-for-unit-tests-minimum-32-chars-long".to_string()),
+            ..Default::default()
+        };
+        let state = AuthState::new(config);
+        let expires_at = Utc::now().timestamp() + 600;
+        let actor = Actor {
+            sub: "admin".to_string(),
+            impersonation_id: "imp_1".to_string(),
+        };
+
+        let token = state
+            .generate_impersonation_token(
+                "alice",
+                "alice@example.com",
+                "Alice",
+                "user",
+                actor.clone(),
+                expires_at,
+            )
+            .unwrap();
+        let claims = state.validate_token(&token).unwrap();
+        assert_eq!(claims.sub, "alice");
+        assert_eq!(claims.exp, expires_at);
+        assert!(!claims.is_admin());
+        assert_eq!(CurrentUser { claims }.impersonator(), Some(&actor));
+
+        // Ordinary tokens carry no actor.
+        let token = state
+            .generate_token("alice", "alice@example.com", "Alice", "user")
+            .unwrap();
+        assert!(state.validate_token(&token).unwrap().act.is_none());
+    }
+
     #[test]
     fn test_current_user() {
         let claims = Claims {
@@ -642,6 +861,8 @@ mod tests {
             preferred_username: None,
             roles: vec!["admin".to_string()],
             role: None,
+            act: None,
+            scope: None,
         };
 
         let user = CurrentUser { claims };
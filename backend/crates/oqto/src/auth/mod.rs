@@ -1,16 +1,35 @@
 //! Authentication module.
 //!
 //! Provides JWT validation middleware with support for:
-//! - OIDC token validation (production)
+//! - Sign-in through an OpenID Connect provider
+//! - Passwordless sign-in with passkeys (WebAuthn)
 //! - Dev bypass mode with configurable test users
+//! - Server-side token revocation with live-connection notification
 
 mod claims;
 mod config;
 mod error;
 mod middleware;
+mod oidc;
+mod passkeys;
+mod rbac;
+mod revocation;
 
-pub use claims::{Claims, Role};
+pub use claims::{Actor, Claims, Role};
 #[allow(unused_imports)]
-pub use config::{AuthConfig, ConfigValidationError, DevUser};
+pub use config::{AuthConfig, ConfigValidationError, DevUser, resolve_secret};
 pub use error::AuthError;
-pub use middleware::{AuthMiddlewareState, AuthState, CurrentUser, RequireAdmin, auth_middleware};
+pub use middleware::{
+    AuthMiddlewareState, AuthState, CurrentUser, RequireAdmin, api_key_claims, auth_middleware,
+    check_impersonation,
+};
+pub use oidc::{
+    DeviceAuthorization, DevicePoll, GroupRoles, LOGIN_COOKIE, LOGIN_TTL, OidcIdentity,
+    OidcProvider, login_cookie,
+};
+pub use passkeys::{PasskeyInfo, PasskeyService};
+pub use rbac::{
+    Access, CreateRoleRequest, Permission, Permissions, RbacRepository, RbacService, RoleInfo,
+    UpdateRoleRequest, valid_role_name,
+};
+pub use revocation::{RevocationRegistry, RevocationScope, TokenRevocation};
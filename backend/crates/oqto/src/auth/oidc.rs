@@ -0,0 +1,908 @@
+//! OpenID Connect sign-in (authorization code flow with PKCE).
+//!
+//! `/api/auth/oidc/login` sends the browser to the provider with a fresh
+//! `state`, `nonce` and PKCE challenge. They travel in a signed, HttpOnly
+//! cookie ([`LOGIN_COOKIE`]) until the provider redirects back to
+//! `/api/auth/oidc/callback`, which only accepts a `state` matching the
+//! cookie: a callback URL with someone else's code and state does not sign
+//! in another browser, and any replica can finish the sign-in. The callback
+//! exchanges the code for an ID token, which is checked against the
+//! provider's JWKS, issuer, client ID and nonce. The resulting
+//! [`OidcIdentity`] is mapped to an oqto user by the login handler, which
+//! issues the usual session token.
+//!
+//! Clients without a browser, like `oqtoctl login --sso`, use the device
+//! authorization grant (RFC 8628) instead: `/api/auth/oidc/device` starts it
+//! at the provider and returns the code the user enters there, and the
+//! client polls `/api/auth/oidc/device/token` until the user has signed in.
+//! The server makes both provider calls, so confidential clients work too.
+//! The device code handed to the client is signed the same way, so polls
+//! may reach any replica.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::time::{Duration, Instant};
+
+use anyhow::{Context, Result, anyhow, bail};
+use base64::{Engine, engine::general_purpose::URL_SAFE_NO_PAD};
+use hmac::{Hmac, Mac};
+use jsonwebtoken::{Algorithm, DecodingKey, Validation, jwk::JwkSet};
+use rand::{Rng, distr::Alphanumeric};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use tokio::sync::RwLock;
+
+use super::config::{ConfigValidationError, resolve_secret};
+
+/// How long a started sign-in may take at the provider.
+pub const LOGIN_TTL: Duration = Duration::from_secs(10 * 60);
+
+/// Cookie carrying a browser sign-in in progress.
+pub const LOGIN_COOKIE: &str = "oqto_oidc_login";
+
+/// How long discovery metadata is reused.
+const METADATA_TTL: Duration = Duration::from_secs(3600);
+
+/// Shortest time between JWKS fetches for an unknown key ID.
+const JWKS_REFRESH_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Grant type of device sign-in token requests.
+const DEVICE_CODE_GRANT: &str = "urn:ietf:params:oauth:grant-type:device_code";
+
+/// Poll interval when the provider does not name one (RFC 8628, 3.2).
+const DEFAULT_DEVICE_POLL_INTERVAL: u64 = 5;
+
+/// Accepted ID token signature algorithms. HMAC is refused: it would make
+/// the client secret a signing key.
+const ALLOWED_ALGORITHMS: [Algorithm; 8] = [
+    Algorithm::RS256,
+    Algorithm::RS384,
+    Algorithm::RS512,
+    Algorithm::PS256,
+    Algorithm::PS384,
+    Algorithm::PS512,
+    Algorithm::ES256,
+    Algorithm::ES384,
+];
+
+/// `[auth.oidc]` configuration.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct OidcConfig {
+    /// Offer sign-in through the provider.
+    pub enabled: bool,
+    /// Issuer URL; `<issuer>/.well-known/openid-configuration` is fetched.
+    pub issuer: String,
+    pub client_id: String,
+    /// Client secret for confidential clients; `env:VAR_NAME` is expanded.
+    /// Public clients rely on PKCE alone.
+    pub client_secret: Option<String>,
+    /// This server's callback URL as registered with the provider,
+    /// e.g. `https://oqto.example.com/api/auth/oidc/callback`.
+    pub redirect_uri: String,
+    pub scopes: Vec<String>,
+    /// Text of the sign-in button.
+    pub label: String,
+    /// Create an account on first sign-in. Otherwise only users that
+    /// already exist (matched by subject or verified email) can sign in.
+    pub auto_provision: bool,
+    /// ID token claim holding the user's groups. Dots address nested
+    /// claims, e.g. `realm_access.roles`.
+    pub groups_claim: String,
+    /// Only members of one of these groups may sign in (empty allows all).
+    pub allowed_groups: Vec<String>,
+    /// oqto role per IdP group: `admin`, `user` or an RBAC role name. When
+    /// set, each sign-in replaces the user's role and RBAC roles with the
+    /// mapped ones.
+    pub group_roles: BTreeMap<String, String>,
+}
+
+impl Default for OidcConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            issuer: String::new(),
+            client_id: String::new(),
+            client_secret: None,
+            redirect_uri: String::new(),
+            scopes: vec![
+                "openid".to_string(),
+                "profile".to_string(),
+                "email".to_string(),
+            ],
+            label: "Single sign-on".to_string(),
+            auto_provision: true,
+            groups_claim: "groups".to_string(),
+            allowed_groups: Vec::new(),
+            group_roles: BTreeMap::new(),
+        }
+    }
+}
+
+/// Roles granted by a user's IdP groups.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct GroupRoles {
+    /// A group maps to `admin`.
+    pub admin: bool,
+    /// RBAC roles, sorted.
+    pub roles: Vec<String>,
+}
+
+impl OidcConfig {
+    pub fn validate(&self) -> Result<(), ConfigValidationError> {
+        let invalid = |reason: &str| Err(ConfigValidationError::InvalidOidc(reason.to_string()));
+        if !self.issuer.starts_with("https://") && !self.issuer.starts_with("http://") {
+            return invalid("issuer must be an http(s) URL");
+        }
+        if self.client_id.is_empty() {
+            return invalid("client_id is required");
+        }
+        if !self.redirect_uri.starts_with("https://") && !self.redirect_uri.starts_with("http://") {
+            return invalid("redirect_uri must be an absolute http(s) URL");
+        }
+        if !self.scopes.iter().any(|s| s == "openid") {
+            return invalid("scopes must include openid");
+        }
+        if let Some(secret) = &self.client_secret {
+            resolve_secret(secret)?;
+        }
+        Ok(())
+    }
+
+    /// Whether a user in these groups may sign in.
+    pub fn allows(&self, groups: &[String]) -> bool {
+        self.allowed_groups.is_empty() || groups.iter().any(|g| self.allowed_groups.contains(g))
+    }
+
+    /// Roles the groups map to, or None when no mapping is configured.
+    pub fn group_roles(&self, groups: &[String]) -> Option<GroupRoles> {
+        if self.group_roles.is_empty() {
+            return None;
+        }
+        let mut admin = false;
+        let mut roles = BTreeSet::new();
+        for role in groups.iter().filter_map(|g| self.group_roles.get(g)) {
+            match role.as_str() {
+                "admin" => admin = true,
+                "user" => {}
+                other => {
+                    roles.insert(other.to_string());
+                }
+            }
+        }
+        Some(GroupRoles {
+            admin,
+            roles: roles.into_iter().collect(),
+        })
+    }
+}
+
+/// A user as asserted by the provider's ID token.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OidcIdentity {
+    /// `sub` claim; stored as the user's `external_id`.
+    pub subject: String,
+    pub email: String,
+    pub email_verified: bool,
+    pub name: String,
+    pub groups: Vec<String>,
+}
+
+/// Discovery document fields used here.
+#[derive(Debug, Clone, Deserialize)]
+struct ProviderMetadata {
+    issuer: String,
+    authorization_endpoint: String,
+    token_endpoint: String,
+    jwks_uri: String,
+    #[serde(default)]
+    device_authorization_endpoint: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct TokenResponse {
+    id_token: Option<String>,
+}
+
+/// Error body of a token request (RFC 6749, 5.2).
+#[derive(Debug, Deserialize)]
+struct TokenError {
+    error: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct IdTokenClaims {
+    sub: String,
+    #[serde(default)]
+    nonce: Option<String>,
+    #[serde(flatten)]
+    other: serde_json::Map<String, Value>,
+}
+
+/// A browser sign-in in progress, as carried in [`LOGIN_COOKIE`].
+#[derive(Debug, Serialize, Deserialize)]
+struct PendingLogin {
+    state: String,
+    verifier: String,
+    nonce: String,
+    return_to: String,
+    /// Unix time the sign-in expires.
+    expires: i64,
+}
+
+/// A device sign-in in progress, as handed to the client.
+#[derive(Debug, Serialize, Deserialize)]
+struct PendingDevice {
+    device_code: String,
+    /// Unix time the device code expires.
+    expires: i64,
+}
+
+/// A browser sign-in started with [`OidcProvider::begin_login`].
+#[derive(Debug)]
+pub struct BrowserLogin {
+    /// Provider URL to redirect the browser to.
+    pub url: String,
+    /// Value of [`LOGIN_COOKIE`] to set on the redirect.
+    pub cookie: String,
+}
+
+/// A sign-in the provider redirected back from.
+#[derive(Debug)]
+pub struct CompletedLogin {
+    pub identity: OidcIdentity,
+    /// Local path to send the browser to.
+    pub return_to: String,
+}
+
+/// A device sign-in started at the provider: the user opens
+/// `verification_uri` and enters `user_code` while the client polls with
+/// `device_code`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DeviceAuthorization {
+    pub device_code: String,
+    pub user_code: String,
+    /// Some providers call it `verification_url`.
+    #[serde(alias = "verification_url")]
+    pub verification_uri: String,
+    /// `verification_uri` with the user code filled in.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub verification_uri_complete: Option<String>,
+    /// Seconds until the device code expires.
+    pub expires_in: u64,
+    /// Seconds to wait between polls.
+    #[serde(default = "default_device_poll_interval")]
+    pub interval: u64,
+}
+
+fn default_device_poll_interval() -> u64 {
+    DEFAULT_DEVICE_POLL_INTERVAL
+}
+
+/// Where a device sign-in stands.
+#[derive(Debug, PartialEq, Eq)]
+pub enum DevicePoll {
+    /// The user has not finished signing in yet.
+    Pending,
+    /// Polling too often; wait five seconds longer between polls.
+    SlowDown,
+    Complete(OidcIdentity),
+}
+
+/// Talks to the configured provider and tracks sign-ins in progress.
+pub struct OidcProvider {
+    config: OidcConfig,
+    client_secret: Option<String>,
+    http: reqwest::Client,
+    metadata: RwLock<Option<(Instant, ProviderMetadata)>>,
+    jwks: RwLock<Option<(Instant, JwkSet)>>,
+    /// Key signing sign-ins in progress, the same on every replica.
+    signing_key: [u8; 32],
+}
+
+impl OidcProvider {
+    /// `secret` is shared by all replicas (the JWT secret); the key that
+    /// signs sign-ins in progress is derived from it.
+    pub fn new(config: OidcConfig, secret: &str) -> Result<Self> {
+        config.validate()?;
+        let client_secret = config
+            .client_secret
+            .as_deref()
+            .map(resolve_secret)
+            .transpose()?;
+        let http = reqwest::Client::builder()
+            .timeout(Duration::from_secs(10))
+            .build()
+            .context("building OIDC HTTP client")?;
+        Ok(Self {
+            config,
+            client_secret,
+            http,
+            metadata: RwLock::new(None),
+            jwks: RwLock::new(None),
+            signing_key: Sha256::new()
+                .chain_update(b"oqto-oidc-login\0")
+                .chain_update(secret.as_bytes())
+                .finalize()
+                .into(),
+        })
+    }
+
+    pub fn config(&self) -> &OidcConfig {
+        &self.config
+    }
+
+    /// Start a sign-in: the provider URL to redirect the browser to and the
+    /// cookie that binds the sign-in to this browser.
+    pub async fn begin_login(&self, return_to: Option<&str>) -> Result<BrowserLogin> {
+        let metadata = self.metadata().await?;
+        let state = random_token(32);
+        let nonce = random_token(32);
+        let verifier = random_token(64);
+
+        let mut url = reqwest::Url::parse(&metadata.authorization_endpoint)
+            .context("invalid authorization_endpoint")?;
+        url.query_pairs_mut()
+            .append_pair("response_type", "code")
+            .append_pair("client_id", &self.config.client_id)
+            .append_pair("redirect_uri", &self.config.redirect_uri)
+            .append_pair("scope", &self.config.scopes.join(" "))
+            .append_pair("state", &state)
+            .append_pair("nonce", &nonce)
+            .append_pair("code_challenge", &pkce_challenge(&verifier))
+            .append_pair("code_challenge_method", "S256");
+
+        let cookie = self.sign(&PendingLogin {
+            state,
+            verifier,
+            nonce,
+            return_to: local_path(return_to),
+            expires: expires_in(LOGIN_TTL),
+        })?;
+        Ok(BrowserLogin {
+            url: url.into(),
+            cookie,
+        })
+    }
+
+    /// Finish a sign-in: check that `state` belongs to the sign-in in the
+    /// browser's [`LOGIN_COOKIE`], redeem the code and check the ID token.
+    /// The code is single-use at the provider.
+    pub async fn complete_login(
+        &self,
+        state: &str,
+        code: &str,
+        cookie: Option<&str>,
+    ) -> Result<CompletedLogin> {
+        let login: PendingLogin =
+            self.verify(cookie.ok_or_else(|| anyhow!("no sign-in was started in this browser"))?)?;
+        if login.expires < chrono::Utc::now().timestamp() {
+            bail!("expired sign-in; please start again");
+        }
+        // Compared as digests, so the time taken says nothing about the state.
+        if Sha256::digest(login.state.as_bytes()) != Sha256::digest(state.as_bytes()) {
+            bail!("state does not match the sign-in started in this browser");
+        }
+        let metadata = self.metadata().await?;
+
+        let mut request = self.http.post(&metadata.token_endpoint).form(&[
+            ("grant_type", "authorization_code"),
+            ("code", code),
+            ("redirect_uri", self.config.redirect_uri.as_str()),
+            ("client_id", self.config.client_id.as_str()),
+            ("code_verifier", login.verifier.as_str()),
+        ]);
+        if let Some(secret) = &self.client_secret {
+            request = request.basic_auth(&self.config.client_id, Some(secret));
+        }
+        let response = request.send().await.context("token request failed")?;
+        if !response.status().is_success() {
+            let status = response.status();
+            let body = response.text().await.unwrap_or_default();
+            bail!("token endpoint returned {status}: {body}");
+        }
+        let tokens: TokenResponse = response.json().await.context("invalid token response")?;
+        let id_token = tokens
+            .id_token
+            .ok_or_else(|| anyhow!("token response has no id_token"))?;
+
+        let claims = self.verify_id_token(&id_token, &metadata.issuer).await?;
+        if claims.nonce.as_deref() != Some(login.nonce.as_str()) {
+            bail!("ID token nonce does not match");
+        }
+        Ok(CompletedLogin {
+            identity: self.identity(claims)?,
+            return_to: login.return_to,
+        })
+    }
+
+    /// Start a device sign-in at the provider.
+    pub async fn begin_device_login(&self) -> Result<DeviceAuthorization> {
+        let metadata = self.metadata().await?;
+        let endpoint = metadata
+            .device_authorization_endpoint
+            .as_deref()
+            .ok_or_else(|| anyhow!("the provider does not support device sign-in"))?;
+        let scope = self.config.scopes.join(" ");
+        let mut request = self.http.post(endpoint).form(&[
+            ("client_id", self.config.client_id.as_str()),
+            ("scope", scope.as_str()),
+        ]);
+        if let Some(secret) = &self.client_secret {
+            request = request.basic_auth(&self.config.client_id, Some(secret));
+        }
+        let response = request
+            .send()
+            .await
+            .context("device authorization request failed")?;
+        if !response.status().is_success() {
+            let status = response.status();
+            let body = response.text().await.unwrap_or_default();
+            bail!("device authorization endpoint returned {status}: {body}");
+        }
+        let mut authorization: DeviceAuthorization = response
+            .json()
+            .await
+            .context("invalid device authorization response")?;
+
+        authorization.device_code = self.sign(&PendingDevice {
+            device_code: authorization.device_code,
+            expires: expires_in(Duration::from_secs(authorization.expires_in)),
+        })?;
+        Ok(authorization)
+    }
+
+    /// Ask the provider whether the user finished a device sign-in started
+    /// with [`Self::begin_device_login`]. Fails once it was denied or
+    /// expired; a completed sign-in cannot be redeemed twice.
+    pub async fn poll_device_login(&self, device_code: &str) -> Result<DevicePoll> {
+        let device: PendingDevice = self
+            .verify(device_code)
+            .map_err(|_| anyhow!("unknown sign-in; please start again"))?;
+        if device.expires < chrono::Utc::now().timestamp() {
+            bail!("expired sign-in; please start again");
+        }
+        let device_code = device.device_code.as_str();
+        let metadata = self.metadata().await?;
+
+        let mut request = self.http.post(&metadata.token_endpoint).form(&[
+            ("grant_type", DEVICE_CODE_GRANT),
+            ("device_code", device_code),
+            ("client_id", self.config.client_id.as_str()),
+        ]);
+        if let Some(secret) = &self.client_secret {
+            request = request.basic_auth(&self.config.client_id, Some(secret));
+        }
+        let response = request.send().await.context("token request failed")?;
+        if !response.status().is_success() {
+            let status = response.status();
+            let body = response.text().await.unwrap_or_default();
+            if let Some(poll) = device_poll_state(&body) {
+                return Ok(poll);
+            }
+            bail!("token endpoint returned {status}: {body}");
+        }
+        let tokens: TokenResponse = response.json().await.context("invalid token response")?;
+        let id_token = tokens
+            .id_token
+            .ok_or_else(|| anyhow!("token response has no id_token"))?;
+        // No nonce: the device grant has no authorization request to bind
+        // one to.
+        let claims = self.verify_id_token(&id_token, &metadata.issuer).await?;
+        Ok(DevicePoll::Complete(self.identity(claims)?))
+    }
+
+    /// `value` as base64 JSON with an HMAC over it.
+    fn sign<T: Serialize>(&self, value: &T) -> Result<String> {
+        let payload = URL_SAFE_NO_PAD.encode(serde_json::to_vec(value)?);
+        let signature = URL_SAFE_NO_PAD.encode(self.mac(&payload).finalize().into_bytes());
+        Ok(format!("{payload}.{signature}"))
+    }
+
+    /// The value signed by [`Self::sign`], if `token` is untampered.
+    fn verify<T: DeserializeOwned>(&self, token: &str) -> Result<T> {
+        let (payload, signature) = token
+            .split_once('.')
+            .ok_or_else(|| anyhow!("malformed sign-in token"))?;
+        let signature = URL_SAFE_NO_PAD
+            .decode(signature)
+            .context("malformed sign-in token")?;
+        self.mac(payload)
+            .verify_slice(&signature)
+            .map_err(|_| anyhow!("sign-in token signature mismatch"))?;
+        let payload = URL_SAFE_NO_PAD
+            .decode(payload)
+            .context("malformed sign-in token")?;
+        serde_json::from_slice(&payload).context("malformed sign-in token")
+    }
+
+    fn mac(&self, payload: &str) -> Hmac<Sha256> {
+        let mut mac =
+            Hmac::<Sha256>::new_from_slice(&self.signing_key).expect("HMAC accepts any key length");
+        mac.update(payload.as_bytes());
+        mac
+    }
+
+    async fn verify_id_token(&self, token: &str, issuer: &str) -> Result<IdTokenClaims> {
+        let header = jsonwebtoken::decode_header(token).context("malformed ID token")?;
+        if !ALLOWED_ALGORITHMS.contains(&header.alg) {
+            bail!("ID token uses unsupported algorithm {:?}", header.alg);
+        }
+        let key = self.decoding_key(header.kid.as_deref()).await?;
+
+        let mut validation = Validation::new(header.alg);
+        validation.set_issuer(&[issuer]);
+        validation.set_audience(&[&self.config.client_id]);
+        validation.leeway = 60;
+        let data = jsonwebtoken::decode::<IdTokenClaims>(token, &key, &validation)
+            .context("ID token rejected")?;
+        Ok(data.claims)
+    }
+
+    /// Key for an ID token, fetching the JWKS again when the key ID is
+    /// unknown (the provider rotated its keys).
+    async fn decoding_key(&self, kid: Option<&str>) -> Result<DecodingKey> {
+        let find = |jwks: &JwkSet| match kid {
+            Some(kid) => jwks.find(kid).cloned(),
+            None => jwks.keys.first().cloned(),
+        };
+        if let Some((fetched, set)) = self.jwks.read().await.as_ref() {
+            if let Some(jwk) = find(set) {
+                return DecodingKey::from_jwk(&jwk).context("unusable JWK");
+            }
+            if fetched.elapsed() < JWKS_REFRESH_INTERVAL {
+                bail!("ID token signed with unknown key {kid:?}");
+            }
+        }
+
+        let metadata = self.metadata().await?;
+        let set: JwkSet = self
+            .http
+            .get(&metadata.jwks_uri)
+            .send()
+            .await
+            .and_then(|r| r.error_for_status())
+            .context("fetching JWKS")?
+            .json()
+            .await
+            .context("invalid JWKS")?;
+        let jwk = find(&set);
+        *self.jwks.write().await = Some((Instant::now(), set));
+        let jwk = jwk.ok_or_else(|| anyhow!("ID token signed with unknown key {kid:?}"))?;
+        DecodingKey::from_jwk(&jwk).context("unusable JWK")
+    }
+
+    async fn metadata(&self) -> Result<ProviderMetadata> {
+        if let Some((fetched, metadata)) = self.metadata.read().await.as_ref()
+            && fetched.elapsed() < METADATA_TTL
+        {
+            return Ok(metadata.clone());
+        }
+        let url = format!(
+            "{}/.well-known/openid-configuration",
+            self.config.issuer.trim_end_matches('/')
+        );
+        let metadata: ProviderMetadata = self
+            .http
+            .get(&url)
+            .send()
+            .await
+            .and_then(|r| r.error_for_status())
+            .with_context(|| format!("fetching {url}"))?
+            .json()
+            .await
+            .context("invalid discovery document")?;
+        if metadata.issuer.trim_end_matches('/') != self.config.issuer.trim_end_matches('/') {
+            bail!(
+                "discovery document is for issuer {}, not {}",
+                metadata.issuer,
+                self.config.issuer
+            );
+        }
+        *self.metadata.write().await = Some((Instant::now(), metadata.clone()));
+        Ok(metadata)
+    }
+
+    fn identity(&self, claims: IdTokenClaims) -> Result<OidcIdentity> {
+        let text = |name: &str| {
+            claims
+                .other
+                .get(name)
+                .and_then(Value::as_str)
+                .filter(|v| !v.is_empty())
+        };
+        let email = text("email")
+            .or_else(|| text("preferred_username").filter(|v| v.contains('@')))
+            .ok_or_else(|| anyhow!("the provider did not return an email address"))?
+            .to_string();
+        let name = text("name")
+            .or_else(|| text("preferred_username"))
+            .unwrap_or(&email)
+            .to_string();
+        let email_verified = match claims.other.get("email_verified") {
+            Some(Value::Bool(verified)) => *verified,
+            Some(Value::String(verified)) => verified == "true",
+            _ => false,
+        };
+        Ok(OidcIdentity {
+            groups: claim_strings(&claims.other, &self.config.groups_claim),
+            subject: claims.sub,
+            email,
+            email_verified,
+            name,
+        })
+    }
+}
+
+/// Strings of a claim that is a string or an array of strings; `path` may
+/// address nested objects with dots.
+fn claim_strings(claims: &serde_json::Map<String, Value>, path: &str) -> Vec<String> {
+    let mut parts = path.split('.');
+    let mut value = parts.next().and_then(|first| claims.get(first));
+    for part in parts {
+        value = value.and_then(|v| v.get(part));
+    }
+    match value {
+        Some(Value::String(s)) => vec![s.clone()],
+        Some(Value::Array(items)) => items
+            .iter()
+            .filter_map(Value::as_str)
+            .map(str::to_string)
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+/// The state a failed device token request reports, if it is still going.
+fn device_poll_state(body: &str) -> Option<DevicePoll> {
+    let error: TokenError = serde_json::from_str(body).ok()?;
+    match error.error.as_str() {
+        "authorization_pending" => Some(DevicePoll::Pending),
+        "slow_down" => Some(DevicePoll::SlowDown),
+        _ => None,
+    }
+}
+
+/// A path on this server to return to after sign-in; anything else, like
+/// `//evil.example` or a full URL, becomes `/`.
+pub fn local_path(return_to: Option<&str>) -> String {
+    match return_to {
+        Some(path)
+            if path.starts_with('/')
+                && !path.starts_with("//")
+                && !path.starts_with("/\\")
+                && !path.chars().any(char::is_control) =>
+        {
+            path.to_string()
+        }
+        _ => "/".to_string(),
+    }
+}
+
+/// The [`LOGIN_COOKIE`] sent with a request.
+pub fn login_cookie(headers: &axum::http::HeaderMap) -> Option<&str> {
+    let header = headers.get(axum::http::header::COOKIE)?.to_str().ok()?;
+    super::middleware::token_from_cookie_header(header, LOGIN_COOKIE)
+}
+
+/// Unix time `ttl` from now.
+fn expires_in(ttl: Duration) -> i64 {
+    chrono::Utc::now().timestamp() + ttl.as_secs() as i64
+}
+
+fn random_token(len: usize) -> String {
+    rand::rng()
+        .sample_iter(&Alphanumeric)
+        .take(len)
+        .map(char::from)
+        .collect()
+}
+
+/// S256 code challenge of a PKCE verifier (RFC 7636).
+fn pkce_challenge(verifier: &str) -> String {
+    URL_SAFE_NO_PAD.encode(Sha256::digest(verifier.as_bytes()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config() -> OidcConfig {
+        OidcConfig {
+            enabled: true,
+            issuer: "https://idp.example.com/realms/oqto".to_string(),
+            client_id: "oqto".to_string(),
+            redirect_uri: "https://oqto.example.com/api/auth/oidc/callback".to_string(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_pkce_challenge_matches_rfc_example() {
+        // RFC 7636, appendix B.
+        assert_eq!(
+            pkce_challenge("dBjftJeZ4CVP-mB92K27uhbUJU1p1r_wW1gFWFOEjXk"),
+            "E9Melhoa2OwvFrEMTJguCHaoeK1t8URWbuGJSstw-cM"
+        );
+    }
+
+    #[test]
+    fn test_local_path_refuses_other_hosts() {
+        assert_eq!(local_path(Some("/sessions/abc?x=1")), "/sessions/abc?x=1");
+        assert_eq!(local_path(Some("//evil.example/x")), "/");
+        assert_eq!(local_path(Some("/\\evil.example")), "/");
+        assert_eq!(local_path(Some("https://evil.example")), "/");
+        assert_eq!(local_path(Some("/a\nb")), "/");
+        assert_eq!(local_path(None), "/");
+    }
+
+    #[test]
+    fn test_device_authorization_and_poll_states() {
+        let authorization: DeviceAuthorization = serde_json::from_value(serde_json::json!({
+            "device_code": "dc",
+            "user_code": "WDJB-MJHT",
+            "verification_url": "https://idp.example.com/device",
+            "expires_in": 1800,
+        }))
+        .unwrap();
+        assert_eq!(
+            authorization.verification_uri,
+            "https://idp.example.com/device"
+        );
+        assert_eq!(authorization.interval, DEFAULT_DEVICE_POLL_INTERVAL);
+
+        assert_eq!(
+            device_poll_state(r#"{"error":"authorization_pending"}"#),
+            Some(DevicePoll::Pending)
+        );
+        assert_eq!(
+            device_poll_state(r#"{"error":"slow_down","error_description":"wait"}"#),
+            Some(DevicePoll::SlowDown)
+        );
+        assert_eq!(device_poll_state(r#"{"error":"access_denied"}"#), None);
+        assert_eq!(device_poll_state("<html>"), None);
+    }
+
+    #[test]
+    fn test_validate() {
+        assert!(config().validate().is_ok());
+        assert!(
+            OidcConfig {
+                client_id: String::new(),
+                ..config()
+            }
+            .validate()
+            .is_err()
+        );
+        assert!(
+            OidcConfig {
+                redirect_uri: "/api/auth/oidc/callback".to_string(),
+                ..config()
+            }
+            .validate()
+            .is_err()
+        );
+        assert!(
+            OidcConfig {
+                scopes: vec!["email".to_string()],
+                ..config()
+            }
+            .validate()
+            .is_err()
+        );
+    }
+
+    #[test]
+    fn test_group_mapping() {
+        let groups = |names: &[&str]| names.iter().map(|n| n.to_string()).collect::<Vec<_>>();
+        let mut config = config();
+        assert!(config.allows(&[]));
+        assert_eq!(config.group_roles(&groups(&["ops"])), None);
+
+        config.allowed_groups = groups(&["staff", "ops"]);
+        config.group_roles = BTreeMap::from([
+            ("ops".to_string(), "admin".to_string()),
+            ("support".to_string(), "operator".to_string()),
+            ("staff".to_string(), "user".to_string()),
+        ]);
+        assert!(config.allows(&groups(&["staff"])));
+        assert!(!config.allows(&groups(&["support"])));
+        assert!(!config.allows(&[]));
+
+        assert_eq!(
+            config.group_roles(&groups(&["staff"])),
+            Some(GroupRoles::default())
+        );
+        assert_eq!(
+            config.group_roles(&groups(&["support", "ops", "unknown"])),
+            Some(GroupRoles {
+                admin: true,
+                roles: vec!["operator".to_string()],
+            })
+        );
+    }
+
+    #[test]
+    fn test_identity_from_claims() {
+        let provider = OidcProvider::new(
+            OidcConfig {
+                groups_claim: "realm_access.roles".to_string(),
+                ..config()
+            },
+            "secret",
+        )
+        .unwrap();
+        let claims: IdTokenClaims = serde_json::from_value(serde_json::json!({
+            "sub": "f3a1",
+            "nonce": "n",
+            "preferred_username": "alice@example.com",
+            "email_verified": "true",
+            "realm_access": { "roles": ["staff", "ops"] },
+        }))
+        .unwrap();
+        assert_eq!(
+            provider.identity(claims).unwrap(),
+            OidcIdentity {
+                subject: "f3a1".to_string(),
+                email: "alice@example.com".to_string(),
+                email_verified: true,
+                name: "alice@example.com".to_string(),
+                groups: vec!["staff".to_string(), "ops".to_string()],
+            }
+        );
+
+        let claims: IdTokenClaims =
+            serde_json::from_value(serde_json::json!({ "sub": "f3a2", "name": "Bob" })).unwrap();
+        assert!(provider.identity(claims).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_login_cookie_binds_state() {
+        let provider = OidcProvider::new(config(), "secret").unwrap();
+        let login = PendingLogin {
+            state: "s1".to_string(),
+            verifier: "v".to_string(),
+            nonce: "n".to_string(),
+            return_to: "/".to_string(),
+            expires: expires_in(LOGIN_TTL),
+        };
+        let cookie = provider.sign(&login).unwrap();
+        let opened: PendingLogin = provider.verify(&cookie).unwrap();
+        assert_eq!(opened.state, "s1");
+
+        // Another replica with the same secret accepts it; another secret does not.
+        let replica = OidcProvider::new(config(), "secret").unwrap();
+        assert!(replica.verify::<PendingLogin>(&cookie).is_ok());
+        let other = OidcProvider::new(config(), "other").unwrap();
+        assert!(other.verify::<PendingLogin>(&cookie).is_err());
+
+        let (payload, signature) = cookie.split_once('.').unwrap();
+        let forged = URL_SAFE_NO_PAD.encode(
+            serde_json::to_vec(&PendingLogin {
+                state: "s2".to_string(),
+                ..opened
+            })
+            .unwrap(),
+        );
+        assert!(
+            provider
+                .verify::<PendingLogin>(&format!("{forged}.{signature}"))
+                .is_err()
+        );
+        assert!(provider.verify::<PendingLogin>(payload).is_err());
+
+        // Checked before the provider is contacted.
+        let err = provider.complete_login("s2", "code", Some(&cookie)).await;
+        assert!(err.unwrap_err().to_string().contains("does not match"));
+        assert!(provider.complete_login("s1", "code", None).await.is_err());
+        let expired = provider
+            .sign(&PendingLogin {
+                expires: 0,
+                ..provider.verify(&cookie).unwrap()
+            })
+            .unwrap();
+        let err = provider.complete_login("s1", "code", Some(&expired)).await;
+        assert!(err.unwrap_err().to_string().contains("expired"));
+    }
+}
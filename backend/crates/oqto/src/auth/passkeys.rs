@@ -0,0 +1,452 @@
+//! Passkey (WebAuthn) sign-in.
+//!
+//! A signed-in user adds a passkey with `/api/auth/passkeys/register/start`,
+//! hands the returned options to `navigator.credentials.create()` and sends
+//! the result to `/api/auth/passkeys/register/finish`. Signing in works the
+//! same way around `navigator.credentials.get()`:
+//! `/api/auth/passkeys/login/start` with a username, then
+//! `/api/auth/passkeys/login/finish`, which issues the usual session token.
+//! The ceremony state is kept here between the two calls; the credentials
+//! themselves are stored per user in `user_passkeys`.
+
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+use anyhow::{Context, Result, anyhow, bail};
+use base64::{Engine, engine::general_purpose::URL_SAFE_NO_PAD};
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+use tokio::sync::Mutex;
+use uuid::Uuid;
+use webauthn_rs::prelude::{
+    CreationChallengeResponse, Passkey, PasskeyAuthentication, PasskeyRegistration,
+    PublicKeyCredential, RegisterPublicKeyCredential, RequestChallengeResponse, Url, Webauthn,
+    WebauthnBuilder,
+};
+
+use crate::db::{self, DbPool, on_pool};
+
+use super::config::ConfigValidationError;
+
+/// How long a started registration or sign-in may take in the browser.
+const CEREMONY_TTL: Duration = Duration::from_secs(5 * 60);
+
+/// Most registrations or sign-ins waiting for the browser at once.
+const MAX_PENDING_CEREMONIES: usize = 10_000;
+
+/// Most passkeys per user.
+const MAX_PASSKEYS_PER_USER: usize = 20;
+
+/// Longest passkey name.
+const MAX_NAME_LEN: usize = 100;
+
+/// `[auth.passkeys]` configuration.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PasskeyConfig {
+    /// Offer passkey registration and sign-in.
+    pub enabled: bool,
+    /// Relying party ID: the domain passkeys are bound to, e.g.
+    /// `oqto.example.com`. Changing it invalidates all registered passkeys.
+    pub rp_id: String,
+    /// Origin the app is served from, e.g. `https://oqto.example.com`. Its
+    /// host must be `rp_id` or a subdomain of it.
+    pub origin: String,
+    /// Name authenticators show for this server.
+    pub rp_name: String,
+}
+
+impl Default for PasskeyConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            rp_id: String::new(),
+            origin: String::new(),
+            rp_name: "oqto".to_string(),
+        }
+    }
+}
+
+impl PasskeyConfig {
+    pub fn validate(&self) -> Result<(), ConfigValidationError> {
+        let invalid =
+            |reason: &str| Err(ConfigValidationError::InvalidPasskeys(reason.to_string()));
+        if self.rp_id.is_empty() {
+            return invalid("rp_id is required");
+        }
+        let Ok(origin) = Url::parse(&self.origin) else {
+            return invalid("origin must be an absolute URL");
+        };
+        let Some(host) = origin.host_str() else {
+            return invalid("origin must have a host");
+        };
+        if host != self.rp_id && !host.ends_with(&format!(".{}", self.rp_id)) {
+            return invalid("the host of origin must be rp_id or a subdomain of it");
+        }
+        // Browsers only offer WebAuthn in secure contexts.
+        if origin.scheme() != "https" && host != "localhost" {
+            return invalid("origin must use https (http only for localhost)");
+        }
+        Ok(())
+    }
+}
+
+/// A registered passkey as shown to its owner.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct PasskeyInfo {
+    pub id: String,
+    pub name: String,
+    pub created_at: String,
+    pub last_used_at: Option<String>,
+}
+
+#[derive(Debug, Clone, FromRow)]
+struct PasskeyRow {
+    id: String,
+    name: String,
+    credential: String,
+    created_at: String,
+    last_used_at: Option<String>,
+}
+
+impl PasskeyRow {
+    fn into_parts(self) -> Result<(PasskeyInfo, Passkey)> {
+        let passkey = serde_json::from_str(&self.credential)
+            .with_context(|| format!("corrupt passkey {}", self.id))?;
+        Ok((
+            PasskeyInfo {
+                id: self.id,
+                name: self.name,
+                created_at: self.created_at,
+                last_used_at: self.last_used_at,
+            },
+            passkey,
+        ))
+    }
+}
+
+/// Stored passkeys.
+#[derive(Debug, Clone)]
+pub struct PasskeyRepository {
+    pool: DbPool,
+}
+
+impl PasskeyRepository {
+    pub fn new(pool: DbPool) -> Self {
+        Self { pool }
+    }
+
+    /// A user's passkeys, oldest first.
+    pub async fn list(&self, user_id: &str) -> Result<Vec<(PasskeyInfo, Passkey)>> {
+        let rows: Vec<PasskeyRow> = on_pool!(&self.pool, |pool| sqlx::query_as(
+            "SELECT id, name, credential, created_at, last_used_at FROM user_passkeys \
+             WHERE user_id = $1 ORDER BY created_at, id"
+        )
+        .bind(user_id)
+        .fetch_all(pool)
+        .await)
+        .context("list passkeys")?;
+        rows.into_iter().map(PasskeyRow::into_parts).collect()
+    }
+
+    pub async fn create(
+        &self,
+        user_id: &str,
+        name: &str,
+        passkey: &Passkey,
+    ) -> Result<PasskeyInfo> {
+        let id = format!("pk_{}", nanoid::nanoid!());
+        let now = db::now();
+        let credential = serde_json::to_string(passkey).context("serialize passkey")?;
+        on_pool!(&self.pool, |pool| sqlx::query(
+            "INSERT INTO user_passkeys (id, user_id, name, credential_id, credential, created_at) \
+             VALUES ($1, $2, $3, $4, $5, $6)"
+        )
+        .bind(&id)
+        .bind(user_id)
+        .bind(name)
+        .bind(credential_id(passkey))
+        .bind(&credential)
+        .bind(&now)
+        .execute(pool)
+        .await
+        .map(|_| ()))
+        .context("insert passkey")?;
+        Ok(PasskeyInfo {
+            id,
+            name: name.to_string(),
+            created_at: now,
+            last_used_at: None,
+        })
+    }
+
+    /// Record a sign-in, with the credential's new signature counter.
+    pub async fn record_use(&self, id: &str, passkey: &Passkey) -> Result<()> {
+        let credential = serde_json::to_string(passkey).context("serialize passkey")?;
+        on_pool!(&self.pool, |pool| sqlx::query(
+            "UPDATE user_passkeys SET credential = $1, last_used_at = $2 WHERE id = $3"
+        )
+        .bind(&credential)
+        .bind(db::now())
+        .bind(id)
+        .execute(pool)
+        .await
+        .map(|_| ()))
+        .context("update passkey")
+    }
+
+    /// Delete one of a user's passkeys. False when the user has no such
+    /// passkey.
+    pub async fn delete(&self, user_id: &str, id: &str) -> Result<bool> {
+        let affected = on_pool!(&self.pool, |pool| sqlx::query(
+            "DELETE FROM user_passkeys WHERE id = $1 AND user_id = $2"
+        )
+        .bind(id)
+        .bind(user_id)
+        .execute(pool)
+        .await
+        .map(|result| result.rows_affected()))
+        .context("delete passkey")?;
+        Ok(affected > 0)
+    }
+}
+
+/// Base64url ID of a credential, as stored in `credential_id`.
+fn credential_id(passkey: &Passkey) -> String {
+    URL_SAFE_NO_PAD.encode(passkey.cred_id())
+}
+
+/// WebAuthn user handle of a user. Authenticators want a stable opaque ID,
+/// so it is derived from the user ID instead of stored.
+fn user_handle(user_id: &str) -> Uuid {
+    Uuid::new_v5(&Uuid::NAMESPACE_OID, user_id.as_bytes())
+}
+
+/// A registration or sign-in waiting for the browser.
+struct Pending<T> {
+    user_id: String,
+    state: T,
+    started: Instant,
+}
+
+/// Take out a pending ceremony that has not expired.
+fn take_pending<T>(pending: &mut HashMap<String, Pending<T>>, id: &str) -> Option<Pending<T>> {
+    pending.retain(|_, p| p.started.elapsed() < CEREMONY_TTL);
+    pending.remove(id)
+}
+
+/// Store a pending ceremony under a fresh ID.
+fn put_pending<T>(
+    pending: &mut HashMap<String, Pending<T>>,
+    user_id: &str,
+    state: T,
+) -> Result<String> {
+    pending.retain(|_, p| p.started.elapsed() < CEREMONY_TTL);
+    if pending.len() >= MAX_PENDING_CEREMONIES {
+        bail!("too many passkey ceremonies in progress");
+    }
+    let id = nanoid::nanoid!(32);
+    pending.insert(
+        id.clone(),
+        Pending {
+            user_id: user_id.to_string(),
+            state,
+            started: Instant::now(),
+        },
+    );
+    Ok(id)
+}
+
+/// Registers passkeys and checks sign-ins with them.
+pub struct PasskeyService {
+    webauthn: Webauthn,
+    repo: PasskeyRepository,
+    registrations: Mutex<HashMap<String, Pending<PasskeyRegistration>>>,
+    logins: Mutex<HashMap<String, Pending<PasskeyAuthentication>>>,
+}
+
+impl PasskeyService {
+    pub fn new(config: &PasskeyConfig, pool: DbPool) -> Result<Self> {
+        config.validate()?;
+        let origin = Url::parse(&config.origin).context("invalid origin")?;
+        let webauthn = WebauthnBuilder::new(&config.rp_id, &origin)
+            .context("invalid passkey relying party")?
+            .rp_name(&config.rp_name)
+            .build()
+            .context("building WebAuthn relying party")?;
+        Ok(Self {
+            webauthn,
+            repo: PasskeyRepository::new(pool),
+            registrations: Mutex::new(HashMap::new()),
+            logins: Mutex::new(HashMap::new()),
+        })
+    }
+
+    /// A user's passkeys.
+    pub async fn list(&self, user_id: &str) -> Result<Vec<PasskeyInfo>> {
+        Ok(self
+            .repo
+            .list(user_id)
+            .await?
+            .into_iter()
+            .map(|(info, _)| info)
+            .collect())
+    }
+
+    pub async fn delete(&self, user_id: &str, id: &str) -> Result<bool> {
+        self.repo.delete(user_id, id).await
+    }
+
+    /// Start adding a passkey for a user. Returns the registration ID and
+    /// the options for `navigator.credentials.create()`.
+    pub async fn begin_registration(
+        &self,
+        user_id: &str,
+        username: &str,
+        display_name: &str,
+    ) -> Result<(String, CreationChallengeResponse)> {
+        let existing = self.repo.list(user_id).await?;
+        if existing.len() >= MAX_PASSKEYS_PER_USER {
+            bail!("You cannot register more than {MAX_PASSKEYS_PER_USER} passkeys");
+        }
+        // Keeps an authenticator from registering twice.
+        let exclude = existing
+            .iter()
+            .map(|(_, passkey)| passkey.cred_id().clone())
+            .collect();
+        let (options, state) = self
+            .webauthn
+            .start_passkey_registration(user_handle(user_id), username, display_name, Some(exclude))
+            .map_err(|e| anyhow!("starting passkey registration: {e}"))?;
+        let id = put_pending(&mut *self.registrations.lock().await, user_id, state)?;
+        Ok((id, options))
+    }
+
+    /// Check the browser's new credential and store it.
+    pub async fn finish_registration(
+        &self,
+        user_id: &str,
+        registration_id: &str,
+        name: &str,
+        credential: &RegisterPublicKeyCredential,
+    ) -> Result<PasskeyInfo> {
+        let name = name.trim();
+        if name.is_empty() || name.chars().count() > MAX_NAME_LEN {
+            bail!("Passkey name must be 1 to {MAX_NAME_LEN} characters");
+        }
+        let pending = take_pending(&mut *self.registrations.lock().await, registration_id)
+            .filter(|pending| pending.user_id == user_id)
+            .context("Passkey registration expired or unknown; please start again")?;
+        let passkey = self
+            .webauthn
+            .finish_passkey_registration(credential, &pending.state)
+            .map_err(|e| anyhow!("Passkey registration was rejected: {e}"))?;
+        self.repo.create(user_id, name, &passkey).await
+    }
+
+    /// Start signing a user in. Returns the sign-in ID and the options for
+    /// `navigator.credentials.get()`, or None when the user has no passkeys.
+    pub async fn begin_login(
+        &self,
+        user_id: &str,
+    ) -> Result<Option<(String, RequestChallengeResponse)>> {
+        let passkeys: Vec<Passkey> = self
+            .repo
+            .list(user_id)
+            .await?
+            .into_iter()
+            .map(|(_, passkey)| passkey)
+            .collect();
+        if passkeys.is_empty() {
+            return Ok(None);
+        }
+        let (options, state) = self
+            .webauthn
+            .start_passkey_authentication(&passkeys)
+            .map_err(|e| anyhow!("starting passkey sign-in: {e}"))?;
+        let id = put_pending(&mut *self.logins.lock().await, user_id, state)?;
+        Ok(Some((id, options)))
+    }
+
+    /// Check the browser's assertion. Returns the ID of the user it signs in.
+    pub async fn finish_login(
+        &self,
+        login_id: &str,
+        credential: &PublicKeyCredential,
+    ) -> Result<String> {
+        let pending = take_pending(&mut *self.logins.lock().await, login_id)
+            .context("passkey sign-in expired or unknown")?;
+        let result = self
+            .webauthn
+            .finish_passkey_authentication(credential, &pending.state)
+            .map_err(|e| anyhow!("passkey assertion rejected: {e}"))?;
+
+        // The passkey may have been deleted since the sign-in started.
+        let (info, mut passkey) = self
+            .repo
+            .list(&pending.user_id)
+            .await?
+            .into_iter()
+            .find(|(_, passkey)| passkey.cred_id() == result.cred_id())
+            .context("passkey no longer registered")?;
+        passkey.update_credential(&result);
+        self.repo.record_use(&info.id, &passkey).await?;
+        Ok(pending.user_id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(rp_id: &str, origin: &str) -> PasskeyConfig {
+        PasskeyConfig {
+            enabled: true,
+            rp_id: rp_id.to_string(),
+            origin: origin.to_string(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn origin_must_belong_to_the_relying_party() {
+        assert!(
+            config("example.com", "https://oqto.example.com")
+                .validate()
+                .is_ok()
+        );
+        assert!(
+            config("oqto.example.com", "https://oqto.example.com:8443")
+                .validate()
+                .is_ok()
+        );
+        assert!(
+            config("localhost", "http://localhost:3000")
+                .validate()
+                .is_ok()
+        );
+
+        assert!(config("", "https://oqto.example.com").validate().is_err());
+        assert!(
+            config("example.com", "https://badexample.com")
+                .validate()
+                .is_err()
+        );
+        assert!(
+            config("example.com", "http://oqto.example.com")
+                .validate()
+                .is_err()
+        );
+        assert!(
+            config("example.com", "oqto.example.com")
+                .validate()
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn user_handles_are_stable_per_user() {
+        assert_eq!(user_handle("usr_1"), user_handle("usr_1"));
+        assert_ne!(user_handle("usr_1"), user_handle("usr_2"));
+    }
+}
@@ -0,0 +1,568 @@
+//! Role-based access control.
+//!
+//! Admins may do everything. Other users get permissions through roles:
+//! named sets of permissions (`roles`, `role_permissions`) assigned to
+//! users in `user_roles`. The built-in `operator` role holds every
+//! permission, which covers day-to-day administration without user,
+//! provider or server management.
+//!
+//! The API resolves the permissions of each request once (see
+//! `api::rbac`); handlers check them with the [`Access`] extractor.
+
+use std::collections::BTreeSet;
+use std::time::{Duration, Instant};
+
+use anyhow::{Context, Result};
+use axum::{extract::FromRequestParts, http::request::Parts};
+use dashmap::DashMap;
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+
+use crate::db::{self, DbPool, on_pool};
+
+use super::{AuthError, CurrentUser};
+
+/// How long resolved permissions are reused before asking the database
+/// again. Role changes made through the API take effect immediately.
+const CACHE_TTL: Duration = Duration::from_secs(30);
+
+/// Longest accepted role name.
+pub const MAX_ROLE_NAME_LEN: usize = 64;
+
+/// Something a role can allow.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub enum Permission {
+    /// List and force-stop any user's sessions, clean up local sessions.
+    #[serde(rename = "sessions.manage")]
+    SessionsManage,
+    /// Refresh the project templates repository.
+    #[serde(rename = "templates.manage")]
+    TemplatesManage,
+    /// Create, import, export and revoke invite codes.
+    #[serde(rename = "invites.manage")]
+    InvitesManage,
+    /// Edit admin-scoped settings and reload them.
+    #[serde(rename = "settings.edit")]
+    SettingsEdit,
+}
+
+impl Permission {
+    pub const ALL: [Permission; 4] = [
+        Permission::SessionsManage,
+        Permission::TemplatesManage,
+        Permission::InvitesManage,
+        Permission::SettingsEdit,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Permission::SessionsManage => "sessions.manage",
+            Permission::TemplatesManage => "templates.manage",
+            Permission::InvitesManage => "invites.manage",
+            Permission::SettingsEdit => "settings.edit",
+        }
+    }
+}
+
+impl std::fmt::Display for Permission {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl std::str::FromStr for Permission {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Permission::ALL
+            .into_iter()
+            .find(|p| p.as_str() == s)
+            .ok_or_else(|| format!("unknown permission: {}", s))
+    }
+}
+
+/// The permissions of one user.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Permissions {
+    admin: bool,
+    granted: BTreeSet<Permission>,
+}
+
+impl Permissions {
+    /// Every permission.
+    pub fn admin() -> Self {
+        Self {
+            admin: true,
+            granted: Permission::ALL.into_iter().collect(),
+        }
+    }
+
+    pub fn granted(permissions: impl IntoIterator<Item = Permission>) -> Self {
+        Self {
+            admin: false,
+            granted: permissions.into_iter().collect(),
+        }
+    }
+
+    pub fn allows(&self, permission: Permission) -> bool {
+        self.admin || self.granted.contains(&permission)
+    }
+
+    pub fn list(&self) -> Vec<Permission> {
+        self.granted.iter().copied().collect()
+    }
+}
+
+/// A role with its permissions.
+#[derive(Debug, Clone, Serialize)]
+pub struct RoleInfo {
+    pub name: String,
+    pub description: Option<String>,
+    pub builtin: bool,
+    pub permissions: Vec<Permission>,
+    pub created_at: String,
+}
+
+/// Request to create a role.
+#[derive(Debug, Clone, Deserialize)]
+pub struct CreateRoleRequest {
+    pub name: String,
+    #[serde(default)]
+    pub description: Option<String>,
+    #[serde(default)]
+    pub permissions: Vec<Permission>,
+}
+
+/// Request to change a role.
+#[derive(Debug, Clone, Deserialize)]
+pub struct UpdateRoleRequest {
+    #[serde(default)]
+    pub description: Option<String>,
+    pub permissions: Vec<Permission>,
+}
+
+/// Whether `name` is a valid role name: lowercase letters, digits, `-` and
+/// `_`, at most [`MAX_ROLE_NAME_LEN`] characters.
+pub fn valid_role_name(name: &str) -> bool {
+    !name.is_empty()
+        && name.len() <= MAX_ROLE_NAME_LEN
+        && name
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-' || c == '_')
+}
+
+#[derive(Debug, FromRow)]
+struct RoleRow {
+    name: String,
+    description: Option<String>,
+    builtin: bool,
+    created_at: String,
+}
+
+/// Storage of roles and their assignment to users.
+#[derive(Debug, Clone)]
+pub struct RbacRepository {
+    pool: DbPool,
+}
+
+impl RbacRepository {
+    pub fn new(pool: DbPool) -> Self {
+        Self { pool }
+    }
+
+    pub async fn list_roles(&self) -> Result<Vec<RoleInfo>> {
+        let rows = on_pool!(&self.pool, |pool| sqlx::query_as::<_, RoleRow>(
+            "SELECT name, description, builtin, created_at FROM roles ORDER BY name"
+        )
+        .fetch_all(pool)
+        .await)
+        .context("list roles")?;
+
+        let mut roles = Vec::with_capacity(rows.len());
+        for row in rows {
+            let permissions = self.role_permissions(&row.name).await?;
+            roles.push(role_info(row, permissions));
+        }
+        Ok(roles)
+    }
+
+    pub async fn get_role(&self, name: &str) -> Result<Option<RoleInfo>> {
+        let row = on_pool!(&self.pool, |pool| sqlx::query_as::<_, RoleRow>(
+            "SELECT name, description, builtin, created_at FROM roles WHERE name = $1"
+        )
+        .bind(name)
+        .fetch_optional(pool)
+        .await)
+        .context("get role")?;
+        match row {
+            Some(row) => {
+                let permissions = self.role_permissions(&row.name).await?;
+                Ok(Some(role_info(row, permissions)))
+            }
+            None => Ok(None),
+        }
+    }
+
+    pub async fn create_role(&self, request: &CreateRoleRequest) -> Result<()> {
+        on_pool!(&self.pool, |pool| {
+            let mut tx = pool.begin().await?;
+            sqlx::query(
+                "INSERT INTO roles (name, description, builtin, created_at) VALUES ($1, $2, $3, $4)",
+            )
+            .bind(&request.name)
+            .bind(&request.description)
+            .bind(false)
+            .bind(db::now())
+            .execute(&mut *tx)
+            .await?;
+            for permission in &request.permissions {
+                sqlx::query("INSERT INTO role_permissions (role, permission) VALUES ($1, $2)")
+                    .bind(&request.name)
+                    .bind(permission.as_str())
+                    .execute(&mut *tx)
+                    .await?;
+            }
+            tx.commit().await
+        })
+        .context("create role")
+    }
+
+    /// Replace the description and permissions of a role. Returns false
+    /// when there is no such role.
+    pub async fn update_role(&self, name: &str, request: &UpdateRoleRequest) -> Result<bool> {
+        on_pool!(&self.pool, |pool| {
+            let mut tx = pool.begin().await?;
+            let updated = sqlx::query("UPDATE roles SET description = $1 WHERE name = $2")
+                .bind(&request.description)
+                .bind(name)
+                .execute(&mut *tx)
+                .await?
+                .rows_affected();
+            if updated == 0 {
+                return Ok(false);
+            }
+            sqlx::query("DELETE FROM role_permissions WHERE role = $1")
+                .bind(name)
+                .execute(&mut *tx)
+                .await?;
+            for permission in &request.permissions {
+                sqlx::query("INSERT INTO role_permissions (role, permission) VALUES ($1, $2)")
+                    .bind(name)
+                    .bind(permission.as_str())
+                    .execute(&mut *tx)
+                    .await?;
+            }
+            tx.commit().await.map(|()| true)
+        })
+        .context("update role")
+    }
+
+    /// Delete a role that is not built in. Its assignments go with it.
+    pub async fn delete_role(&self, name: &str) -> Result<bool> {
+        let deleted = on_pool!(&self.pool, |pool| sqlx::query(
+            "DELETE FROM roles WHERE name = $1 AND builtin = $2"
+        )
+        .bind(name)
+        .bind(false)
+        .execute(pool)
+        .await
+        .map(|r| r.rows_affected()))
+        .context("delete role")?;
+        Ok(deleted > 0)
+    }
+
+    /// Roles assigned to a user, by name.
+    pub async fn user_roles(&self, user_id: &str) -> Result<Vec<String>> {
+        on_pool!(&self.pool, |pool| sqlx::query_scalar::<_, String>(
+            "SELECT role FROM user_roles WHERE user_id = $1 ORDER BY role"
+        )
+        .bind(user_id)
+        .fetch_all(pool)
+        .await)
+        .context("list user roles")
+    }
+
+    /// Replace the roles of a user.
+    pub async fn set_user_roles(
+        &self,
+        user_id: &str,
+        roles: &[String],
+        granted_by: &str,
+    ) -> Result<()> {
+        on_pool!(&self.pool, |pool| {
+            let mut tx = pool.begin().await?;
+            sqlx::query("DELETE FROM user_roles WHERE user_id = $1")
+                .bind(user_id)
+                .execute(&mut *tx)
+                .await?;
+            for role in roles {
+                sqlx::query(
+                    "INSERT INTO user_roles (user_id, role, granted_by, created_at) VALUES ($1, $2, $3, $4)",
+                )
+                .bind(user_id)
+                .bind(role)
+                .bind(granted_by)
+                .bind(db::now())
+                .execute(&mut *tx)
+                .await?;
+            }
+            tx.commit().await
+        })
+        .context("set user roles")
+    }
+
+    /// Everything the user's roles allow.
+    pub async fn permissions_for_user(&self, user_id: &str) -> Result<Permissions> {
+        let names = on_pool!(&self.pool, |pool| sqlx::query_scalar::<_, String>(
+            r#"SELECT DISTINCT rp.permission
+               FROM user_roles ur JOIN role_permissions rp ON rp.role = ur.role
+               WHERE ur.user_id = $1"#
+        )
+        .bind(user_id)
+        .fetch_all(pool)
+        .await)
+        .context("list user permissions")?;
+        // Permissions this build does not know are ignored.
+        Ok(Permissions::granted(
+            names.iter().filter_map(|name| name.parse().ok()),
+        ))
+    }
+
+    async fn role_permissions(&self, role: &str) -> Result<Vec<Permission>> {
+        let names = on_pool!(&self.pool, |pool| sqlx::query_scalar::<_, String>(
+            "SELECT permission FROM role_permissions WHERE role = $1"
+        )
+        .bind(role)
+        .fetch_all(pool)
+        .await)
+        .context("list role permissions")?;
+        let mut permissions: Vec<Permission> =
+            names.iter().filter_map(|name| name.parse().ok()).collect();
+        permissions.sort();
+        Ok(permissions)
+    }
+}
+
+fn role_info(row: RoleRow, permissions: Vec<Permission>) -> RoleInfo {
+    RoleInfo {
+        name: row.name,
+        description: row.description,
+        builtin: row.builtin,
+        permissions,
+        created_at: row.created_at,
+    }
+}
+
+/// Resolves and caches the permissions of users.
+pub struct RbacService {
+    repo: RbacRepository,
+    cache: DashMap<String, (Instant, Permissions)>,
+}
+
+impl RbacService {
+    pub fn new(repo: RbacRepository) -> Self {
+        Self {
+            repo,
+            cache: DashMap::new(),
+        }
+    }
+
+    pub fn repo(&self) -> &RbacRepository {
+        &self.repo
+    }
+
+    /// Permissions of the user. A failed lookup grants nothing.
+    pub async fn permissions_for(&self, user: &CurrentUser) -> Permissions {
+        if user.is_admin() {
+            return Permissions::admin();
+        }
+        if let Some(entry) = self.cache.get(user.id())
+            && entry.0.elapsed() < CACHE_TTL
+        {
+            return entry.1.clone();
+        }
+        match self.repo.permissions_for_user(user.id()).await {
+            Ok(permissions) => {
+                self.cache
+                    .insert(user.id().to_string(), (Instant::now(), permissions.clone()));
+                permissions
+            }
+            Err(e) => {
+                tracing::warn!(user_id = %user.id(), "Failed to load permissions: {e:#}");
+                Permissions::default()
+            }
+        }
+    }
+
+    /// Forget cached permissions after a role change.
+    pub fn invalidate(&self, user_id: Option<&str>) {
+        match user_id {
+            Some(user_id) => {
+                self.cache.remove(user_id);
+            }
+            None => self.cache.clear(),
+        }
+    }
+}
+
+/// The authenticated user with their permissions.
+///
+/// Use as an extractor in handlers that admins and suitably permitted
+/// users may call, then check with [`Access::require`].
+#[derive(Debug, Clone)]
+pub struct Access {
+    pub user: CurrentUser,
+    pub permissions: Permissions,
+}
+
+impl Access {
+    pub fn allows(&self, permission: Permission) -> bool {
+        self.permissions.allows(permission)
+    }
+
+    /// The user, if they hold `permission`.
+    pub fn require(&self, permission: Permission) -> Result<&CurrentUser, AuthError> {
+        if self.allows(permission) {
+            Ok(&self.user)
+        } else {
+            Err(AuthError::InsufficientPermissions(format!(
+                "'{permission}' permission required"
+            )))
+        }
+    }
+}
+
+impl<S> FromRequestParts<S> for Access
+where
+    S: Send + Sync,
+{
+    type Rejection = AuthError;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        let user = parts
+            .extensions
+            .get::<CurrentUser>()
+            .cloned()
+            .ok_or(AuthError::MissingAuthHeader)?;
+        // Without the RBAC middleware only admins are allowed anything.
+        let permissions = match parts.extensions.get::<Permissions>() {
+            Some(permissions) => permissions.clone(),
+            None if user.is_admin() => Permissions::admin(),
+            None => Permissions::default(),
+        };
+        Ok(Access { user, permissions })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::Database;
+
+    async fn repo() -> RbacRepository {
+        let db = Database::in_memory().await.unwrap();
+        for user in ["alice", "bob"] {
+            sqlx::query(
+                "INSERT INTO users (id, username, email, display_name) VALUES (?, ?, ?, ?)",
+            )
+            .bind(user)
+            .bind(user)
+            .bind(format!("{user}@example.com"))
+            .bind(user)
+            .execute(db.pool())
+            .await
+            .unwrap();
+        }
+        RbacRepository::new(db.shared().clone())
+    }
+
+    #[test]
+    fn test_permissions() {
+        assert!(Permissions::admin().allows(Permission::SettingsEdit));
+        assert!(!Permissions::default().allows(Permission::SessionsManage));
+
+        let granted = Permissions::granted([Permission::InvitesManage]);
+        assert!(granted.allows(Permission::InvitesManage));
+        assert!(!granted.allows(Permission::SessionsManage));
+
+        assert_eq!(
+            "sessions.manage".parse::<Permission>(),
+            Ok(Permission::SessionsManage)
+        );
+        assert!("users.manage".parse::<Permission>().is_err());
+        assert!(valid_role_name("support-tier_1"));
+        assert!(!valid_role_name("Operator"));
+        assert!(!valid_role_name(""));
+    }
+
+    #[tokio::test]
+    async fn test_operator_role() {
+        let repo = repo().await;
+        let operator = repo.get_role("operator").await.unwrap().unwrap();
+        assert!(operator.builtin);
+        assert_eq!(operator.permissions.len(), Permission::ALL.len());
+        assert!(!repo.delete_role("operator").await.unwrap());
+
+        assert_eq!(
+            repo.permissions_for_user("alice").await.unwrap(),
+            Permissions::default()
+        );
+        repo.set_user_roles("alice", &["operator".to_string()], "admin")
+            .await
+            .unwrap();
+        assert_eq!(repo.user_roles("alice").await.unwrap(), vec!["operator"]);
+        assert!(
+            repo.permissions_for_user("alice")
+                .await
+                .unwrap()
+                .allows(Permission::SettingsEdit)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_custom_roles() {
+        let repo = repo().await;
+        repo.create_role(&CreateRoleRequest {
+            name: "support".to_string(),
+            description: None,
+            permissions: vec![Permission::SessionsManage],
+        })
+        .await
+        .unwrap();
+        repo.set_user_roles("bob", &["support".to_string()], "admin")
+            .await
+            .unwrap();
+        let permissions = repo.permissions_for_user("bob").await.unwrap();
+        assert!(permissions.allows(Permission::SessionsManage));
+        assert!(!permissions.allows(Permission::InvitesManage));
+
+        assert!(
+            repo.update_role(
+                "support",
+                &UpdateRoleRequest {
+                    description: Some("Invites only".to_string()),
+                    permissions: vec![Permission::InvitesManage],
+                },
+            )
+            .await
+            .unwrap()
+        );
+        let permissions = repo.permissions_for_user("bob").await.unwrap();
+        assert_eq!(permissions.list(), vec![Permission::InvitesManage]);
+
+        assert!(repo.delete_role("support").await.unwrap());
+        assert!(repo.user_roles("bob").await.unwrap().is_empty());
+        assert!(
+            !repo
+                .update_role(
+                    "support",
+                    &UpdateRoleRequest {
+                        description: None,
+                        permissions: vec![],
+                    },
+                )
+                .await
+                .unwrap()
+        );
+    }
+}
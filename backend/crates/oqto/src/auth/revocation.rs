@@ -0,0 +1,162 @@
+//! Token revocation tracking.
+//!
+//! JWTs are stateless, so revoking them before `exp` requires a server-side
+//! cutoff: any token for a user issued up to the cutoff is rejected.
+//! Revocations are also broadcast so long-lived connections (WebSockets)
+//! can drop immediately instead of waiting for their next request.
+//!
+//! Cutoffs and the broadcast are process-local: they are neither persisted
+//! nor shared through `[db] url`. With several replicas, a revocation only
+//! takes effect on the replica that made it; the others keep accepting the
+//! user's tokens until `exp`, and a restart forgets every cutoff. Keep token
+//! lifetimes short when running replicas.
+
+use chrono::Utc;
+use dashmap::DashMap;
+use tokio::sync::broadcast;
+
+use super::Claims;
+
+/// Size of the revocation broadcast channel.
+const REVOCATION_BUFFER_SIZE: usize = 256;
+
+/// Which credentials a revocation applies to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RevocationScope {
+    /// Every token issued to the user so far (role change, deactivation, ...).
+    AllTokens,
+    /// Only connections authenticated with an API key.
+    ApiKeys,
+    /// Only connections an admin opened while impersonating the user; the
+    /// impersonation itself is ended in the database.
+    Impersonation,
+}
+
+/// A revocation notice broadcast to live connections.
+#[derive(Debug, Clone)]
+pub struct TokenRevocation {
+    pub user_id: String,
+    pub scope: RevocationScope,
+    pub reason: String,
+    /// Unix timestamp at which the revocation took effect.
+    pub revoked_at: i64,
+}
+
+/// Per-user revocation cutoffs plus a broadcast channel for live connections,
+/// held in this process only.
+pub struct RevocationRegistry {
+    revoked_before: DashMap<String, i64>,
+    tx: broadcast::Sender<TokenRevocation>,
+}
+
+impl Default for RevocationRegistry {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl RevocationRegistry {
+    pub fn new() -> Self {
+        let (tx, _) = broadcast::channel(REVOCATION_BUFFER_SIZE);
+        Self {
+            revoked_before: DashMap::new(),
+            tx,
+        }
+    }
+
+    /// Revoke credentials for a user and notify live connections.
+    pub fn revoke(&self, user_id: &str, scope: RevocationScope, reason: &str) -> TokenRevocation {
+        let revoked_at = Utc::now().timestamp();
+        if scope == RevocationScope::AllTokens {
+            self.revoked_before.insert(user_id.to_string(), revoked_at);
+        }
+
+        let revocation = TokenRevocation {
+            user_id: user_id.to_string(),
+            scope,
+            reason: reason.to_string(),
+            revoked_at,
+        };
+        // No receivers is fine: nobody is connected.
+        let _ = self.tx.send(revocation.clone());
+        revocation
+    }
+
+    /// Check whether the given claims were issued at or before the user's cutoff.
+    ///
+    /// `iat` has second granularity, so a token minted in the same second as
+    /// the revocation is rejected too. Tokens without `iat` are treated as
+    /// revoked once a cutoff exists, since their age cannot be established.
+    pub fn is_revoked(&self, claims: &Claims) -> bool {
+        let Some(cutoff) = self.revoked_before.get(&claims.sub) else {
+            return false;
+        };
+        match claims.iat {
+            Some(iat) => iat <= *cutoff,
+            None => true,
+        }
+    }
+
+    /// Subscribe to revocation notices.
+    pub fn subscribe(&self) -> broadcast::Receiver<TokenRevocation> {
+        self.tx.subscribe()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn claims(sub: &str, iat: Option<i64>) -> Claims {
+        Claims {
+            sub: sub.to_string(),
+            iss: None,
+            aud: None,
+            exp: Utc::now().timestamp() + 3600,
+            iat,
+            nbf: None,
+            jti: None,
+            email: None,
+            name: None,
+            preferred_username: None,
+            roles: vec![],
+            role: None,
+            act: None,
+            scope: None,
+        }
+    }
+
+    #[test]
+    fn test_revoke_all_tokens_sets_cutoff() {
+        let registry = RevocationRegistry::new();
+        let old = claims("alice", Some(Utc::now().timestamp() - 10));
+        assert!(!registry.is_revoked(&old));
+
+        registry.revoke("alice", RevocationScope::AllTokens, "role changed");
+        assert!(registry.is_revoked(&old));
+        assert!(registry.is_revoked(&claims("alice", None)));
+
+        // Tokens issued after the cutoff and other users are unaffected.
+        assert!(!registry.is_revoked(&claims("alice", Some(Utc::now().timestamp() + 5))));
+        assert!(!registry.is_revoked(&claims("bob", Some(0))));
+    }
+
+    #[test]
+    fn test_revoke_api_keys_does_not_set_cutoff() {
+        let registry = RevocationRegistry::new();
+        registry.revoke("alice", RevocationScope::ApiKeys, "api key revoked");
+        assert!(!registry.is_revoked(&claims("alice", Some(0))));
+    }
+
+    #[tokio::test]
+    async fn test_revoke_broadcasts() {
+        let registry = RevocationRegistry::new();
+        let mut rx = registry.subscribe();
+        registry.revoke("alice", RevocationScope::AllTokens, "deactivated");
+
+        let notice = rx.recv().await.unwrap();
+        assert_eq!(notice.user_id, "alice");
+        assert_eq!(notice.scope, RevocationScope::AllTokens);
+        assert_eq!(notice.reason, "deactivated");
+    }
+}
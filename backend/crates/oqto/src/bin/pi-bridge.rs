@@ -492,7 +492,8 @@ async fn main() -> Result<()> {
         .with_state(state.clone());
 
     // Start server
-    let addr: SocketAddr = format!("{}:{}", args.host, args.port).parse()?;
+    let host = args.host.trim_start_matches('[').trim_end_matches(']');
+    let addr = SocketAddr::new(host.parse()?, args.port);
     let listener = TcpListener::bind(addr).await?;
     info!("pi-bridge listening on {}", addr);
 
@@ -0,0 +1,14 @@
+//! Named bookmarks at points in a session's timeline.
+//!
+//! Long agent runs are hard to navigate, so users can mark a message with a
+//! label and come back to it. Every bookmark resolves to a deep link
+//! (`/sessions?bookmark={id}`) that opens the session and scrolls to the
+//! message. Bookmarks in shared workspace sessions are visible to every
+//! member with `chat_read`, including read-only members; bookmarks in
+//! personal sessions only to their owner.
+
+mod models;
+mod repository;
+
+pub use models::{CreateBookmarkRequest, SessionBookmark, UpdateBookmarkRequest};
+pub use repository::{BookmarkRepository, NewBookmark};
@@ -0,0 +1,42 @@
+use serde::{Deserialize, Serialize};
+
+/// A named point in a session's timeline.
+#[derive(Debug, Clone, Serialize)]
+pub struct SessionBookmark {
+    pub id: String,
+    pub session_id: String,
+    /// Who created it.
+    pub user_id: String,
+    /// Shared workspace of the session; None for personal sessions.
+    pub workspace_id: Option<String>,
+    pub label: String,
+    /// Message the bookmark points at.
+    pub message_id: String,
+    /// Position of the message in the transcript, if the client knew it.
+    pub message_index: Option<i64>,
+    pub created_at: String,
+    /// Frontend path that opens the session at the message.
+    pub link: String,
+}
+
+impl SessionBookmark {
+    /// Deep link for a bookmark ID.
+    pub fn link_for(id: &str) -> String {
+        format!("/sessions?bookmark={}", urlencoding::encode(id))
+    }
+}
+
+/// Request body for creating a bookmark.
+#[derive(Debug, Clone, Deserialize)]
+pub struct CreateBookmarkRequest {
+    pub label: String,
+    pub message_id: String,
+    #[serde(default)]
+    pub message_index: Option<i64>,
+}
+
+/// Request body for renaming a bookmark.
+#[derive(Debug, Clone, Deserialize)]
+pub struct UpdateBookmarkRequest {
+    pub label: String,
+}
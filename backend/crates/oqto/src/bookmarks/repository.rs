@@ -0,0 +1,218 @@
+use anyhow::{Context, Result};
+use sqlx::{FromRow, SqlitePool};
+
+use super::SessionBookmark;
+
+const BOOKMARK_COLUMNS: &str =
+    "id, session_id, user_id, workspace_id, label, message_id, message_index, created_at";
+
+#[derive(Debug, Clone, FromRow)]
+struct BookmarkRow {
+    id: String,
+    session_id: String,
+    user_id: String,
+    workspace_id: Option<String>,
+    label: String,
+    message_id: String,
+    message_index: Option<i64>,
+    created_at: String,
+}
+
+impl From<BookmarkRow> for SessionBookmark {
+    fn from(row: BookmarkRow) -> Self {
+        Self {
+            link: SessionBookmark::link_for(&row.id),
+            id: row.id,
+            session_id: row.session_id,
+            user_id: row.user_id,
+            workspace_id: row.workspace_id,
+            label: row.label,
+            message_id: row.message_id,
+            message_index: row.message_index,
+            created_at: row.created_at,
+        }
+    }
+}
+
+/// Where a new bookmark points.
+#[derive(Debug, Clone)]
+pub struct NewBookmark<'a> {
+    pub session_id: &'a str,
+    pub user_id: &'a str,
+    pub workspace_id: Option<&'a str>,
+    pub label: &'a str,
+    pub message_id: &'a str,
+    pub message_index: Option<i64>,
+}
+
+#[derive(Debug, Clone)]
+pub struct BookmarkRepository {
+    pool: SqlitePool,
+}
+
+impl BookmarkRepository {
+    pub fn new(pool: SqlitePool) -> Self {
+        Self { pool }
+    }
+
+    fn generate_id() -> String {
+        format!("bmk_{}", nanoid::nanoid!(12))
+    }
+
+    pub async fn create(&self, bookmark: &NewBookmark<'_>) -> Result<SessionBookmark> {
+        let id = Self::generate_id();
+        sqlx::query(
+            r#"INSERT INTO session_bookmarks
+                   (id, session_id, user_id, workspace_id, label, message_id, message_index)
+               VALUES (?, ?, ?, ?, ?, ?, ?)"#,
+        )
+        .bind(&id)
+        .bind(bookmark.session_id)
+        .bind(bookmark.user_id)
+        .bind(bookmark.workspace_id)
+        .bind(bookmark.label)
+        .bind(bookmark.message_id)
+        .bind(bookmark.message_index)
+        .execute(&self.pool)
+        .await
+        .context("insert session bookmark")?;
+
+        self.get(&id)
+            .await?
+            .ok_or_else(|| anyhow::anyhow!("Bookmark not found after creation"))
+    }
+
+    pub async fn get(&self, id: &str) -> Result<Option<SessionBookmark>> {
+        let row = sqlx::query_as::<_, BookmarkRow>(&format!(
+            "SELECT {BOOKMARK_COLUMNS} FROM session_bookmarks WHERE id = ?"
+        ))
+        .bind(id)
+        .fetch_optional(&self.pool)
+        .await
+        .context("get session bookmark")?;
+        Ok(row.map(Into::into))
+    }
+
+    /// Bookmarks `viewer` can see in a session, in timeline order: all of the
+    /// shared ones and the viewer's own.
+    pub async fn list_for_session(
+        &self,
+        session_id: &str,
+        viewer: &str,
+    ) -> Result<Vec<SessionBookmark>> {
+        let rows = sqlx::query_as::<_, BookmarkRow>(&format!(
+            "SELECT {BOOKMARK_COLUMNS} FROM session_bookmarks
+             WHERE session_id = ? AND (workspace_id IS NOT NULL OR user_id = ?)
+             ORDER BY message_index IS NULL, message_index, created_at, id"
+        ))
+        .bind(session_id)
+        .bind(viewer)
+        .fetch_all(&self.pool)
+        .await
+        .context("list session bookmarks")?;
+        Ok(rows.into_iter().map(Into::into).collect())
+    }
+
+    pub async fn count_for_session(&self, session_id: &str) -> Result<i64> {
+        sqlx::query_scalar("SELECT COUNT(*) FROM session_bookmarks WHERE session_id = ?")
+            .bind(session_id)
+            .fetch_one(&self.pool)
+            .await
+            .context("count session bookmarks")
+    }
+
+    pub async fn rename(&self, id: &str, label: &str) -> Result<()> {
+        sqlx::query("UPDATE session_bookmarks SET label = ? WHERE id = ?")
+            .bind(label)
+            .bind(id)
+            .execute(&self.pool)
+            .await
+            .context("rename session bookmark")?;
+        Ok(())
+    }
+
+    pub async fn delete(&self, id: &str) -> Result<bool> {
+        let result = sqlx::query("DELETE FROM session_bookmarks WHERE id = ?")
+            .bind(id)
+            .execute(&self.pool)
+            .await
+            .context("delete session bookmark")?;
+        Ok(result.rows_affected() > 0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::Database;
+
+    async fn repo() -> BookmarkRepository {
+        let db = Database::in_memory().await.unwrap();
+        for user in ["alice", "bob"] {
+            sqlx::query(
+                "INSERT INTO users (id, username, email, display_name) VALUES (?, ?, ?, ?)",
+            )
+            .bind(user)
+            .bind(user)
+            .bind(format!("{user}@example.com"))
+            .bind(user)
+            .execute(db.pool())
+            .await
+            .unwrap();
+        }
+        BookmarkRepository::new(db.pool().clone())
+    }
+
+    fn bookmark<'a>(
+        user_id: &'a str,
+        workspace_id: Option<&'a str>,
+        label: &'a str,
+        message_index: Option<i64>,
+    ) -> NewBookmark<'a> {
+        NewBookmark {
+            session_id: "ses_1",
+            user_id,
+            workspace_id,
+            label,
+            message_id: "msg_1",
+            message_index,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_bookmarks_visibility_and_order() {
+        let repo = repo().await;
+        let late = repo
+            .create(&bookmark("alice", Some("sw_1"), "tests pass", Some(40)))
+            .await
+            .unwrap();
+        assert_eq!(late.link, format!("/sessions?bookmark={}", late.id));
+        repo.create(&bookmark("bob", Some("sw_1"), "plan", Some(3)))
+            .await
+            .unwrap();
+        repo.create(&bookmark("alice", None, "private", None))
+            .await
+            .unwrap();
+
+        let labels = |bookmarks: Vec<SessionBookmark>| {
+            bookmarks.into_iter().map(|b| b.label).collect::<Vec<_>>()
+        };
+        assert_eq!(
+            labels(repo.list_for_session("ses_1", "alice").await.unwrap()),
+            vec!["plan", "tests pass", "private"]
+        );
+        assert_eq!(
+            labels(repo.list_for_session("ses_1", "bob").await.unwrap()),
+            vec!["plan", "tests pass"]
+        );
+        assert_eq!(repo.count_for_session("ses_1").await.unwrap(), 3);
+
+        repo.rename(&late.id, "all tests pass").await.unwrap();
+        assert_eq!(
+            repo.get(&late.id).await.unwrap().unwrap().label,
+            "all tests pass"
+        );
+        assert!(repo.delete(&late.id).await.unwrap());
+        assert!(!repo.delete(&late.id).await.unwrap());
+    }
+}
@@ -0,0 +1,355 @@
+//! Registry of optional subsystems and whether they can be used.
+//!
+//! mmry, voice, hstry, EAVS and sldr are all optional, and a misconfigured or
+//! unreachable one used to show up only as a broken feature later. At
+//! startup each subsystem is registered as enabled, disabled or degraded
+//! (with the reason), and services with a network endpoint are probed on an
+//! interval so the state follows them going down and coming back.
+//!
+//! The registry is served by `GET /api/meta/capabilities`, sent in the
+//! WebSocket `connected` event, and [`require_capabilities`] answers routes
+//! of an unusable subsystem with 503 and the reason.
+
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+
+use axum::{
+    Json,
+    extract::{Request, State},
+    http::StatusCode,
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use tracing::{info, warn};
+
+use crate::api::{AppState, ErrorResponse};
+
+/// How often endpoints of enabled subsystems are probed.
+pub const PROBE_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Upper bound on a single probe.
+const PROBE_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// Optional subsystems tracked by the registry.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Subsystem {
+    Mmry,
+    Voice,
+    Hstry,
+    Eavs,
+    Sldr,
+}
+
+impl Subsystem {
+    pub const ALL: [Subsystem; 5] = [
+        Subsystem::Mmry,
+        Subsystem::Voice,
+        Subsystem::Hstry,
+        Subsystem::Eavs,
+        Subsystem::Sldr,
+    ];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Subsystem::Mmry => "mmry",
+            Subsystem::Voice => "voice",
+            Subsystem::Hstry => "hstry",
+            Subsystem::Eavs => "eavs",
+            Subsystem::Sldr => "sldr",
+        }
+    }
+}
+
+impl std::fmt::Display for Subsystem {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CapabilityState {
+    Enabled,
+    /// Turned off in the configuration.
+    Disabled,
+    /// Configured but not (fully) working.
+    Degraded,
+}
+
+impl std::fmt::Display for CapabilityState {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            CapabilityState::Enabled => "enabled",
+            CapabilityState::Disabled => "disabled",
+            CapabilityState::Degraded => "degraded",
+        })
+    }
+}
+
+/// State of one subsystem.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Capability {
+    pub subsystem: Subsystem,
+    pub state: CapabilityState,
+    /// Why the subsystem is disabled or degraded.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+    /// When the subsystem entered this state.
+    pub since: DateTime<Utc>,
+}
+
+/// An endpoint whose reachability decides between enabled and degraded.
+pub struct ProbeTarget {
+    pub subsystem: Subsystem,
+    /// What the endpoint is, for the reason ("STT server").
+    pub label: &'static str,
+    /// Current URL of the endpoint; None while the subsystem is turned off
+    /// (some settings can be reloaded at runtime).
+    pub url: Box<dyn Fn() -> Option<String> + Send + Sync>,
+}
+
+impl ProbeTarget {
+    /// A target whose URL never changes.
+    pub fn fixed(subsystem: Subsystem, label: &'static str, url: String) -> Self {
+        Self {
+            subsystem,
+            label,
+            url: Box::new(move || Some(url.clone())),
+        }
+    }
+}
+
+/// Shared record of subsystem states.
+#[derive(Debug, Default)]
+pub struct CapabilityRegistry {
+    entries: RwLock<HashMap<Subsystem, Capability>>,
+}
+
+impl CapabilityRegistry {
+    /// Record the state of `subsystem`. Transitions are logged; setting the
+    /// current state again keeps its `since`.
+    pub fn set(&self, subsystem: Subsystem, state: CapabilityState, reason: Option<String>) {
+        let Ok(mut entries) = self.entries.write() else {
+            return;
+        };
+        if let Some(current) = entries.get(&subsystem)
+            && current.state == state
+            && current.reason == reason
+        {
+            return;
+        }
+        match (state, &reason) {
+            (CapabilityState::Degraded, Some(reason)) => {
+                warn!(subsystem = %subsystem, "Subsystem degraded: {reason}")
+            }
+            (state, _) => info!(subsystem = %subsystem, state = %state, "Subsystem state"),
+        }
+        entries.insert(
+            subsystem,
+            Capability {
+                subsystem,
+                state,
+                reason,
+                since: Utc::now(),
+            },
+        );
+    }
+
+    pub fn enable(&self, subsystem: Subsystem) {
+        self.set(subsystem, CapabilityState::Enabled, None);
+    }
+
+    pub fn disable(&self, subsystem: Subsystem, reason: impl Into<String>) {
+        self.set(subsystem, CapabilityState::Disabled, Some(reason.into()));
+    }
+
+    pub fn degrade(&self, subsystem: Subsystem, reason: impl Into<String>) {
+        self.set(subsystem, CapabilityState::Degraded, Some(reason.into()));
+    }
+
+    /// State of `subsystem`; unregistered ones count as disabled.
+    pub fn get(&self, subsystem: Subsystem) -> Capability {
+        self.entries
+            .read()
+            .ok()
+            .and_then(|entries| entries.get(&subsystem).cloned())
+            .unwrap_or_else(|| Capability {
+                subsystem,
+                state: CapabilityState::Disabled,
+                reason: Some("not configured".to_string()),
+                since: Utc::now(),
+            })
+    }
+
+    /// Every subsystem, in [`Subsystem::ALL`] order.
+    pub fn snapshot(&self) -> Vec<Capability> {
+        Subsystem::ALL.into_iter().map(|s| self.get(s)).collect()
+    }
+
+    /// Probe `targets` every [`PROBE_INTERVAL`]: a subsystem is enabled
+    /// while all its endpoints accept connections, degraded otherwise, and
+    /// disabled while a target has no URL. Only pass targets of subsystems
+    /// that are not degraded for other reasons.
+    pub fn start_probe_task(self: &Arc<Self>, targets: Vec<ProbeTarget>) {
+        if targets.is_empty() {
+            return;
+        }
+        let registry = Arc::clone(self);
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(PROBE_INTERVAL);
+            interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+            loop {
+                interval.tick().await;
+                // None marks a subsystem that is turned off.
+                let mut failures: HashMap<Subsystem, Option<Vec<String>>> = HashMap::new();
+                for target in &targets {
+                    let failed = failures
+                        .entry(target.subsystem)
+                        .or_insert_with(|| Some(Vec::new()));
+                    let Some(url) = (target.url)() else {
+                        *failed = None;
+                        continue;
+                    };
+                    if let Some(failed) = failed
+                        && let Err(e) = probe(&url).await
+                    {
+                        failed.push(format!("{} unreachable at {url}: {e}", target.label));
+                    }
+                }
+                for (subsystem, failed) in failures {
+                    match failed {
+                        None if registry.get(subsystem).state == CapabilityState::Disabled => {}
+                        None => registry.disable(subsystem, "turned off in the configuration"),
+                        Some(failed) if failed.is_empty() => registry.enable(subsystem),
+                        Some(failed) => registry.degrade(subsystem, failed.join("; ")),
+                    }
+                }
+            }
+        });
+    }
+}
+
+/// Open a TCP connection to the host and port of `url`.
+pub async fn probe(url: &str) -> Result<(), String> {
+    let parsed = reqwest::Url::parse(url).map_err(|e| format!("invalid URL ({e})"))?;
+    let host = parsed.host_str().ok_or("URL has no host")?;
+    let host = host.trim_start_matches('[').trim_end_matches(']');
+    let port = parsed.port_or_known_default().ok_or("URL has no port")?;
+    match tokio::time::timeout(PROBE_TIMEOUT, tokio::net::TcpStream::connect((host, port))).await {
+        Ok(Ok(_)) => Ok(()),
+        Ok(Err(e)) => Err(e.to_string()),
+        Err(_) => Err("timed out".to_string()),
+    }
+}
+
+/// Route prefixes (below `/api`) that need a subsystem, and whether they
+/// still work while it is degraded. Memories are files in the workspace and
+/// only need mmry to be turned on.
+const GATED_ROUTES: &[(&str, Subsystem, bool)] = &[
+    ("/voice/", Subsystem::Voice, false),
+    ("/sldr", Subsystem::Sldr, false),
+    ("/admin/eavs/sync-models", Subsystem::Eavs, false),
+    ("/admin/eavs/catalog-lookup", Subsystem::Eavs, false),
+    ("/workspace/memories", Subsystem::Mmry, true),
+];
+
+/// The subsystem a request path needs, and whether degraded is good enough.
+fn gate_for_path(path: &str) -> Option<(Subsystem, bool)> {
+    let path = path.strip_prefix("/api").unwrap_or(path);
+    GATED_ROUTES
+        .iter()
+        .find(|(prefix, _, _)| {
+            path.strip_prefix(prefix).is_some_and(|rest| {
+                prefix.ends_with('/') || rest.is_empty() || rest.starts_with('/')
+            })
+        })
+        .map(|&(_, subsystem, degraded_ok)| (subsystem, degraded_ok))
+}
+
+/// Answer requests to routes of an unusable subsystem with 503 and why.
+pub async fn require_capabilities(
+    State(state): State<AppState>,
+    request: Request,
+    next: Next,
+) -> Response {
+    if let Some((subsystem, degraded_ok)) = gate_for_path(request.uri().path()) {
+        let capability = state.capabilities.get(subsystem);
+        let usable = match capability.state {
+            CapabilityState::Enabled => true,
+            CapabilityState::Degraded => degraded_ok,
+            CapabilityState::Disabled => false,
+        };
+        if !usable {
+            return unavailable(&capability);
+        }
+    }
+    next.run(request).await
+}
+
+fn unavailable(capability: &Capability) -> Response {
+    let body = ErrorResponse {
+        error: format!("{} is {}", capability.subsystem, capability.state),
+        code: "SERVICE_UNAVAILABLE",
+        details: capability.reason.clone(),
+    };
+    (StatusCode::SERVICE_UNAVAILABLE, Json(body)).into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn registry_tracks_transitions() {
+        let registry = CapabilityRegistry::default();
+        assert_eq!(
+            registry.get(Subsystem::Voice).state,
+            CapabilityState::Disabled
+        );
+
+        registry.enable(Subsystem::Voice);
+        let since = registry.get(Subsystem::Voice).since;
+        registry.enable(Subsystem::Voice);
+        assert_eq!(registry.get(Subsystem::Voice).since, since);
+
+        registry.degrade(Subsystem::Voice, "STT server unreachable");
+        let voice = registry.get(Subsystem::Voice);
+        assert_eq!(voice.state, CapabilityState::Degraded);
+        assert_eq!(voice.reason.as_deref(), Some("STT server unreachable"));
+
+        let snapshot = registry.snapshot();
+        assert_eq!(snapshot.len(), Subsystem::ALL.len());
+        assert_eq!(snapshot[1].subsystem, Subsystem::Voice);
+    }
+
+    #[test]
+    fn gates_routes_by_prefix() {
+        assert_eq!(
+            gate_for_path("/api/voice/stt"),
+            Some((Subsystem::Voice, false))
+        );
+        assert_eq!(gate_for_path("/sldr"), Some((Subsystem::Sldr, false)));
+        assert_eq!(gate_for_path("/sldr/decks"), Some((Subsystem::Sldr, false)));
+        assert_eq!(gate_for_path("/sldrx"), None);
+        assert_eq!(
+            gate_for_path("/workspace/memories/search"),
+            Some((Subsystem::Mmry, true))
+        );
+        assert_eq!(gate_for_path("/admin/eavs/providers"), None);
+        assert_eq!(gate_for_path("/sessions"), None);
+    }
+
+    #[tokio::test]
+    async fn probe_reports_unreachable_endpoints() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        assert!(probe(&format!("ws://127.0.0.1:{port}/stt")).await.is_ok());
+        drop(listener);
+        assert!(probe(&format!("http://127.0.0.1:{port}")).await.is_err());
+        assert!(probe("not a url").await.is_err());
+    }
+}
@@ -0,0 +1,301 @@
+//! Applying config.toml changes without restarting the server.
+//!
+//! The config file is watched for changes (and can be reloaded on demand
+//! with `POST /api/admin/config/reload`). A reload parses the whole file
+//! again, then applies the settings that running services can pick up
+//! without dropping sessions: the log level, the session limits, the default
+//! EAVS budget and rate limit of new session keys, and the voice settings.
+//! Changes to any other setting are reported as needing a restart.
+
+use std::collections::BTreeSet;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+use serde::Serialize;
+use tokio::sync::{Mutex, mpsc};
+use tracing::{debug, info, warn};
+
+use crate::api::VoiceState;
+use crate::session::{SessionLimits, SessionService};
+use crate::settings::SettingsService;
+
+/// Settings applied to running services on reload, as `section.key` (or a
+/// whole section).
+pub const LIVE_KEYS: &[&str] = &[
+    "logging.level",
+    "sessions.max_concurrent_sessions",
+    "sessions.idle_timeout_minutes",
+    "sessions.idle_action",
+    "eavs.default_session_budget_usd",
+    "eavs.default_session_rpm",
+    "voice",
+];
+
+/// Quiet period after the last file event before reloading, so editors that
+/// write in several steps trigger one reload.
+const DEBOUNCE: Duration = Duration::from_millis(500);
+
+/// The reloadable part of the config.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LiveConfig {
+    pub log_level: String,
+    pub sessions: SessionLimits,
+    pub voice: VoiceState,
+}
+
+/// A freshly loaded config: its live part and the whole config as TOML.
+pub struct LoadedConfig {
+    pub live: LiveConfig,
+    pub full: toml::Value,
+}
+
+/// Outcome of a reload.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ReloadReport {
+    /// Live settings that changed and were applied.
+    pub applied: Vec<String>,
+    /// Sections with other changes; they take effect after a restart.
+    pub restart_required: Vec<String>,
+}
+
+type Loader = Box<dyn Fn() -> Result<LoadedConfig> + Send + Sync>;
+type LogLevelSetter = Box<dyn Fn(&str) -> Result<()> + Send + Sync>;
+
+/// Reloads the config and pushes live settings into running services.
+pub struct ConfigReloader {
+    load: Loader,
+    set_log_level: LogLevelSetter,
+    sessions: Arc<SessionService>,
+    voice: Arc<RwLock<VoiceState>>,
+    settings: Option<Arc<SettingsService>>,
+    /// Config the server is running with.
+    current: Mutex<LoadedConfig>,
+}
+
+impl ConfigReloader {
+    pub fn new(
+        initial: LoadedConfig,
+        load: Loader,
+        set_log_level: LogLevelSetter,
+        sessions: Arc<SessionService>,
+        voice: Arc<RwLock<VoiceState>>,
+        settings: Option<Arc<SettingsService>>,
+    ) -> Self {
+        Self {
+            load,
+            set_log_level,
+            sessions,
+            voice,
+            settings,
+            current: Mutex::new(initial),
+        }
+    }
+
+    /// Load the config again and apply what changed. An unreadable or
+    /// invalid file leaves everything as it was.
+    pub async fn reload(&self) -> Result<ReloadReport> {
+        let loaded = (self.load)().context("loading config")?;
+        let mut current = self.current.lock().await;
+
+        let mut report = ReloadReport {
+            applied: Vec::new(),
+            restart_required: restart_sections(&current.full, &loaded.full),
+        };
+        let (old, new) = (&current.live, &loaded.live);
+
+        if old.log_level != new.log_level {
+            match (self.set_log_level)(&new.log_level) {
+                Ok(()) => report.applied.push("logging.level".to_string()),
+                Err(e) => warn!("Not applying logging.level: {e:#}"),
+            }
+        }
+        if old.sessions != new.sessions {
+            self.sessions.set_limits(new.sessions);
+            report
+                .applied
+                .extend(changed_limits(&old.sessions, &new.sessions));
+        }
+        if old.voice != new.voice {
+            if let Ok(mut voice) = self.voice.write() {
+                *voice = new.voice.clone();
+            }
+            report.applied.push("voice".to_string());
+        }
+        *current = loaded;
+        drop(current);
+
+        if let Some(settings) = &self.settings
+            && let Err(e) = settings.reload().await
+        {
+            warn!("Failed to reload settings after config change: {e:#}");
+        }
+
+        if !report.applied.is_empty() {
+            info!(applied = ?report.applied, "Applied config changes");
+        }
+        if !report.restart_required.is_empty() {
+            warn!(
+                sections = ?report.restart_required,
+                "Config changes take effect after the next restart"
+            );
+        }
+        Ok(report)
+    }
+
+    /// Reload whenever the config file changes.
+    pub fn watch(self: Arc<Self>, config_file: &Path) -> Result<()> {
+        use notify::{RecursiveMode, Watcher};
+
+        // Watch the directory: editors often replace the file instead of
+        // writing it in place.
+        let dir = config_file
+            .parent()
+            .context("config file has no parent directory")?
+            .to_path_buf();
+        let file_name = config_file
+            .file_name()
+            .context("config file has no file name")?
+            .to_os_string();
+
+        let (tx, mut rx) = mpsc::channel::<notify::Result<notify::Event>>(64);
+        let mut watcher = notify::recommended_watcher(move |res| {
+            let _ = tx.blocking_send(res);
+        })
+        .context("creating config file watcher")?;
+        watcher
+            .watch(&dir, RecursiveMode::NonRecursive)
+            .with_context(|| format!("watching {}", dir.display()))?;
+
+        tokio::spawn(async move {
+            // Dropping the watcher stops the events.
+            let _watcher = watcher;
+            let touches_config =
+                |paths: &[PathBuf]| paths.iter().any(|p| p.file_name() == Some(&file_name));
+            while let Some(event) = rx.recv().await {
+                match event {
+                    Ok(event) if touches_config(&event.paths) => {}
+                    Ok(_) => continue,
+                    Err(e) => {
+                        warn!("Config file watcher error: {e:?}");
+                        continue;
+                    }
+                }
+                // Wait for the writes to settle.
+                while let Ok(Some(_)) = tokio::time::timeout(DEBOUNCE, rx.recv()).await {}
+                debug!("Config file changed, reloading");
+                if let Err(e) = self.reload().await {
+                    warn!("Config reload failed, keeping the running config: {e:#}");
+                }
+            }
+        });
+        Ok(())
+    }
+}
+
+fn changed_limits(old: &SessionLimits, new: &SessionLimits) -> Vec<String> {
+    let mut changed = Vec::new();
+    if old.max_concurrent_sessions != new.max_concurrent_sessions {
+        changed.push("sessions.max_concurrent_sessions".to_string());
+    }
+    if old.idle_timeout_minutes != new.idle_timeout_minutes {
+        changed.push("sessions.idle_timeout_minutes".to_string());
+    }
+    if old.idle_action != new.idle_action {
+        changed.push("sessions.idle_action".to_string());
+    }
+    if old.default_session_budget_usd != new.default_session_budget_usd {
+        changed.push("eavs.default_session_budget_usd".to_string());
+    }
+    if old.default_session_rpm != new.default_session_rpm {
+        changed.push("eavs.default_session_rpm".to_string());
+    }
+    changed
+}
+
+/// Top-level sections that differ in anything but the live keys.
+fn restart_sections(old: &toml::Value, new: &toml::Value) -> Vec<String> {
+    let strip = |value: &toml::Value| {
+        let mut table = value.as_table().cloned().unwrap_or_default();
+        for key in LIVE_KEYS {
+            match key.split_once('.') {
+                Some((section, field)) => {
+                    if let Some(toml::Value::Table(section)) = table.get_mut(section) {
+                        section.remove(field);
+                    }
+                }
+                None => {
+                    table.remove(*key);
+                }
+            }
+        }
+        table
+    };
+    let (old, new) = (strip(old), strip(new));
+    let keys: BTreeSet<&String> = old.keys().chain(new.keys()).collect();
+    keys.into_iter()
+        .filter(|key| old.get(*key) != new.get(*key))
+        .cloned()
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::session::IdleAction;
+
+    fn toml(text: &str) -> toml::Value {
+        text.parse().unwrap()
+    }
+
+    #[test]
+    fn test_restart_sections_ignore_live_keys() {
+        let old = toml(
+            "[logging]\nlevel = \"info\"\n[sessions]\nmax_concurrent_sessions = 3\n\
+             [voice]\nenabled = false\n[server]\nport = 8080\n",
+        );
+        let live_only = toml(
+            "[logging]\nlevel = \"debug\"\n[sessions]\nmax_concurrent_sessions = 5\n\
+             [voice]\nenabled = true\n[server]\nport = 8080\n",
+        );
+        assert!(restart_sections(&old, &live_only).is_empty());
+
+        let structural = toml(
+            "[logging]\nlevel = \"info\"\nfile = \"/tmp/oqto.log\"\n\
+             [sessions]\nmax_concurrent_sessions = 3\n[voice]\nenabled = false\n\
+             [server]\nport = 9090\n",
+        );
+        assert_eq!(
+            restart_sections(&old, &structural),
+            vec!["logging".to_string(), "server".to_string()]
+        );
+    }
+
+    #[test]
+    fn test_changed_limits() {
+        let old = SessionLimits {
+            max_concurrent_sessions: 3,
+            idle_timeout_minutes: 30,
+            idle_action: IdleAction::Stop,
+            default_session_budget_usd: Some(10.0),
+            default_session_rpm: Some(60),
+        };
+        let new = SessionLimits {
+            default_session_budget_usd: Some(25.0),
+            ..old
+        };
+        assert_eq!(
+            changed_limits(&old, &new),
+            vec!["eavs.default_session_budget_usd".to_string()]
+        );
+        let hibernate = SessionLimits {
+            idle_action: IdleAction::Hibernate,
+            ..old
+        };
+        assert_eq!(
+            changed_limits(&old, &hibernate),
+            vec!["sessions.idle_action".to_string()]
+        );
+    }
+}
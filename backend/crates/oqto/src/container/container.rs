@@ -2,6 +2,7 @@
 
 use serde::{Deserialize, Deserializer, Serialize};
 use std::collections::HashMap;
+use ts_rs::TS;
 
 use super::error::{ContainerError, ContainerResult};
 
@@ -97,6 +98,84 @@ impl PortMapping {
     }
 }
 
+/// Resource limits of a container. Unset limits use the runtime's default.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "../../../../frontend/src/generated/")]
+#[serde(default)]
+pub struct ResourceLimits {
+    /// Relative CPU weight (`--cpu-shares`, 1024 is the runtime default).
+    pub cpu_shares: Option<u32>,
+    /// Memory limit in MiB, swap included.
+    pub memory_mb: Option<u32>,
+    /// Maximum number of processes.
+    pub pids_limit: Option<u32>,
+    /// GPUs to pass through: "all" or comma-separated device indices/UUIDs.
+    pub gpus: Option<String>,
+}
+
+impl ResourceLimits {
+    /// These limits, with the ones unset here taken from `fallback`.
+    pub fn or(&self, fallback: &ResourceLimits) -> ResourceLimits {
+        ResourceLimits {
+            cpu_shares: self.cpu_shares.or(fallback.cpu_shares),
+            memory_mb: self.memory_mb.or(fallback.memory_mb),
+            pids_limit: self.pids_limit.or(fallback.pids_limit),
+            gpus: self.gpus.clone().or_else(|| fallback.gpus.clone()),
+        }
+    }
+
+    /// Whether no limit is set.
+    pub fn is_empty(&self) -> bool {
+        *self == ResourceLimits::default()
+    }
+
+    /// GPU device IDs, or `["all"]`.
+    pub fn gpu_devices(&self) -> Vec<&str> {
+        self.gpus
+            .as_deref()
+            .map(|gpus| {
+                gpus.split(',')
+                    .map(str::trim)
+                    .filter(|g| !g.is_empty())
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    /// Check that the limits are within what the runtimes accept.
+    pub fn validate(&self) -> ContainerResult<()> {
+        if self.cpu_shares.is_some_and(|s| !(2..=262_144).contains(&s)) {
+            return Err(ContainerError::InvalidInput(
+                "cpu_shares must be between 2 and 262144".to_string(),
+            ));
+        }
+        if self.memory_mb.is_some_and(|m| m < 6) {
+            return Err(ContainerError::InvalidInput(
+                "memory_mb must be at least 6".to_string(),
+            ));
+        }
+        if self.pids_limit == Some(0) {
+            return Err(ContainerError::InvalidInput(
+                "pids_limit must be at least 1".to_string(),
+            ));
+        }
+        if let Some(gpus) = &self.gpus {
+            let devices = self.gpu_devices();
+            let valid_device = |d: &&str| d.chars().all(|c| c.is_ascii_alphanumeric() || c == '-');
+            if devices.is_empty()
+                || !devices.iter().all(valid_device)
+                || (devices.contains(&"all") && devices.len() > 1)
+            {
+                return Err(ContainerError::InvalidInput(format!(
+                    "invalid gpus '{}': use \"all\" or comma-separated device IDs",
+                    gpus
+                )));
+            }
+        }
+        Ok(())
+    }
+}
+
 /// Configuration for creating a new container.
 #[derive(Debug, Clone, Default)]
 pub struct ContainerConfig {
@@ -114,6 +193,8 @@ pub struct ContainerConfig {
     pub ports: Vec<PortMapping>,
     /// Volume mounts (host_path -> container_path).
     pub volumes: Vec<(String, String)>,
+    /// Read-only volume mounts (host_path -> container_path).
+    pub read_only_volumes: Vec<(String, String)>,
     /// Working directory inside the container.
     pub workdir: Option<String>,
     /// Labels for the container.
@@ -121,6 +202,8 @@ pub struct ContainerConfig {
     pub labels: HashMap<String, String>,
     /// Network mode (e.g., "host", "bridge", "none").
     pub network_mode: Option<String>,
+    /// CPU, memory, process and GPU limits.
+    pub resources: ResourceLimits,
 }
 
 impl ContainerConfig {
@@ -156,7 +239,7 @@ impl ContainerConfig {
         }
 
         // Validate volume paths
-        for (host_path, container_path) in &self.volumes {
+        for (host_path, container_path) in self.volumes.iter().chain(&self.read_only_volumes) {
             validate_volume_path(host_path, "host")?;
             validate_volume_path(container_path, "container")?;
         }
@@ -166,6 +249,8 @@ impl ContainerConfig {
             validate_container_path(workdir)?;
         }
 
+        self.resources.validate()?;
+
         Ok(())
     }
 
@@ -195,7 +280,6 @@ impl ContainerConfig {
     }
 
     /// Add multiple environment variables.
-    #[allow(dead_code)]
     pub fn envs(mut self, envs: HashMap<String, String>) -> Self {
         self.env.extend(envs);
         self
@@ -217,6 +301,17 @@ impl ContainerConfig {
         self
     }
 
+    /// Add a read-only volume mount.
+    pub fn volume_ro(
+        mut self,
+        host_path: impl Into<String>,
+        container_path: impl Into<String>,
+    ) -> Self {
+        self.read_only_volumes
+            .push((host_path.into(), container_path.into()));
+        self
+    }
+
     /// Set the working directory.
     #[allow(dead_code)]
     pub fn workdir(mut self, workdir: impl Into<String>) -> Self {
@@ -231,6 +326,12 @@ impl ContainerConfig {
         self
     }
 
+    /// Set the resource limits.
+    pub fn resources(mut self, resources: ResourceLimits) -> Self {
+        self.resources = resources;
+        self
+    }
+
     /// Add a label.
     #[allow(dead_code)]
     pub fn label(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
@@ -372,6 +473,43 @@ pub struct ContainerStats {
     pub pids: String,
 }
 
+/// How a stopped container exited, from `inspect`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ContainerExitState {
+    /// Exit code of the main process (128 + signal when it was killed).
+    pub exit_code: i32,
+    /// Whether the kernel OOM killer ended the container.
+    pub oom_killed: bool,
+    /// Memory limit in bytes, if one was set.
+    pub memory_limit: Option<u64>,
+    /// Error reported by the runtime (e.g. the entrypoint was not found).
+    pub error: String,
+}
+
+impl ContainerExitState {
+    /// Inspect format producing the line parsed by [`ContainerExitState::parse`].
+    pub(crate) const INSPECT_FORMAT: &'static str =
+        "{{.State.ExitCode}}|{{.State.OOMKilled}}|{{.HostConfig.Memory}}|{{.State.Error}}";
+
+    /// Parse the output of `inspect --format INSPECT_FORMAT`.
+    pub(crate) fn parse(line: &str) -> Option<Self> {
+        let mut fields = line.trim().trim_matches('"').splitn(4, '|');
+        let exit_code = fields.next()?.trim().parse().ok()?;
+        let oom_killed = fields.next()?.trim() == "true";
+        let memory_limit = fields
+            .next()
+            .and_then(|m| m.trim().parse::<u64>().ok())
+            .filter(|m| *m > 0);
+        let error = fields.next().unwrap_or_default().trim().to_string();
+        Some(Self {
+            exit_code,
+            oom_killed,
+            memory_limit,
+            error,
+        })
+    }
+}
+
 // ============================================================================
 // Input Validation Functions
 // ============================================================================
@@ -636,6 +774,73 @@ fn validate_container_path(path: &str) -> ContainerResult<()> {
 mod validation_tests {
     use super::*;
 
+    #[test]
+    fn test_parse_container_exit_state() {
+        let state = ContainerExitState::parse("137|true|2147483648|\n").unwrap();
+        assert_eq!(state.exit_code, 137);
+        assert!(state.oom_killed);
+        assert_eq!(state.memory_limit, Some(2 * 1024 * 1024 * 1024));
+
+        let state = ContainerExitState::parse(
+            "127|false|0|exec: \"pi\": executable file not found in $PATH",
+        )
+        .unwrap();
+        assert!(!state.oom_killed);
+        assert_eq!(state.memory_limit, None);
+        assert!(state.error.contains("executable file not found"));
+
+        assert!(ContainerExitState::parse("").is_none());
+    }
+
+    #[test]
+    fn test_resource_limits() {
+        let defaults = ResourceLimits {
+            cpu_shares: Some(1024),
+            memory_mb: Some(4096),
+            ..Default::default()
+        };
+        let user = ResourceLimits {
+            memory_mb: Some(8192),
+            gpus: Some("all".to_string()),
+            ..Default::default()
+        };
+        let merged = user.or(&defaults);
+        assert_eq!(merged.cpu_shares, Some(1024));
+        assert_eq!(merged.memory_mb, Some(8192));
+        assert_eq!(merged.gpu_devices(), ["all"]);
+        assert!(merged.validate().is_ok());
+        assert!(ResourceLimits::default().is_empty());
+
+        for invalid in [
+            ResourceLimits {
+                cpu_shares: Some(1),
+                ..Default::default()
+            },
+            ResourceLimits {
+                memory_mb: Some(4),
+                ..Default::default()
+            },
+            ResourceLimits {
+                pids_limit: Some(0),
+                ..Default::default()
+            },
+            ResourceLimits {
+                gpus: Some("all,0".to_string()),
+                ..Default::default()
+            },
+            ResourceLimits {
+                gpus: Some("0;rm".to_string()),
+                ..Default::default()
+            },
+            ResourceLimits {
+                gpus: Some(" ".to_string()),
+                ..Default::default()
+            },
+        ] {
+            assert!(invalid.validate().is_err(), "{invalid:?}");
+        }
+    }
+
     #[test]
     fn test_validate_image_name_valid() {
         assert!(validate_image_name("ubuntu").is_ok());
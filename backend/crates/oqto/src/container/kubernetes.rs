@@ -0,0 +1,1313 @@
+//! Container runtime running each session as a pod on a Kubernetes cluster.
+//!
+//! A session container becomes three objects named after it (lowercased,
+//! `_` replaced by `-`):
+//!
+//! - a pod running the image, with `restartPolicy: Never` so an exited
+//!   session stays exited like a container,
+//! - a Service publishing the session's host ports, which also keeps the pod
+//!   manifest in its `oqto.dev/pod` annotation, so stopping (deleting the
+//!   pod) and starting (creating it again) works like with containers,
+//! - a `<name>-home` PersistentVolumeClaim backing the read-write volumes.
+//!   Volumes below `workspace_claim_root` are mounted from the shared
+//!   `workspace_claim` instead, so the backend sees the same files.
+//!
+//! The rest of the backend reaches session services on `localhost:<host
+//! port>`; the runtime listens on those ports and forwards connections to
+//! the Service, so the backend has to run in the cluster (or resolve its
+//! service DNS). Checkpoints and image builds are not supported; PID limits
+//! are left to the kubelet.
+
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+
+use async_trait::async_trait;
+use futures::StreamExt;
+use reqwest::{Method, StatusCode};
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::Mutex;
+use tokio::task::JoinHandle;
+use tokio_rustls::TlsConnector;
+use tokio_rustls::rustls::pki_types::pem::PemObject;
+use tokio_rustls::rustls::pki_types::{CertificateDer, ServerName};
+use tokio_rustls::rustls::{ClientConfig, RootCertStore};
+use tokio_tungstenite::WebSocketStream;
+use tokio_tungstenite::tungstenite::Message;
+use tokio_tungstenite::tungstenite::client::IntoClientRequest;
+use tokio_tungstenite::tungstenite::http::HeaderValue;
+
+use super::container::ContainerPort;
+use super::socket::format_size;
+use super::{
+    Container, ContainerConfig, ContainerError, ContainerExitState, ContainerResult,
+    ContainerRuntimeApi, ContainerStats, ResourceLimits, validate_container_id_or_name,
+};
+use crate::observability::metrics::metrics;
+
+/// Service account files mounted into pods.
+const SERVICE_ACCOUNT_DIR: &str = "/var/run/secrets/kubernetes.io/serviceaccount";
+
+/// Label on every object this runtime creates.
+const MANAGED_BY: (&str, &str) = ("app.kubernetes.io/managed-by", "oqto");
+
+/// Label selecting a session's pod; its value is the object name.
+const POD_LABEL: &str = "oqto.dev/pod";
+
+/// Label with the container name the session asked for.
+const CONTAINER_LABEL: &str = "oqto.dev/container";
+
+/// Service annotation holding the pod manifest.
+const POD_ANNOTATION: &str = "oqto.dev/pod";
+
+/// Name of the session container in the pod.
+const CONTAINER: &str = "session";
+
+/// Kubernetes connection and placement of session pods (`[container.kubernetes]`).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct KubernetesConfig {
+    /// API server URL (default: the in-cluster API from
+    /// `KUBERNETES_SERVICE_HOST`/`KUBERNETES_SERVICE_PORT`).
+    pub api_server: Option<String>,
+    /// Bearer token file, read for every request so rotated tokens are
+    /// picked up (default: the pod's service account token).
+    pub token_file: Option<PathBuf>,
+    /// CA bundle of the API server (default: the service account's).
+    pub ca_file: Option<PathBuf>,
+    /// Namespace of session pods (default: the backend's own namespace).
+    pub namespace: Option<String>,
+    /// Storage class of the per-session home claims (default: the
+    /// cluster's default class).
+    pub storage_class: Option<String>,
+    /// Size of the per-session home claims.
+    pub volume_size: String,
+    /// ReadWriteMany claim that the backend has mounted at
+    /// `workspace_claim_root`.
+    pub workspace_claim: Option<String>,
+    pub workspace_claim_root: Option<PathBuf>,
+    /// Node labels session pods must match.
+    pub node_selector: HashMap<String, String>,
+    /// Service account of session pods. Without one, pods get no API token.
+    pub service_account: Option<String>,
+    /// Secrets for pulling session images.
+    pub image_pull_secrets: Vec<String>,
+}
+
+impl Default for KubernetesConfig {
+    fn default() -> Self {
+        Self {
+            api_server: None,
+            token_file: None,
+            ca_file: None,
+            namespace: None,
+            storage_class: None,
+            volume_size: "10Gi".to_string(),
+            workspace_claim: None,
+            workspace_claim_root: None,
+            node_selector: HashMap::new(),
+            service_account: None,
+            image_pull_secrets: Vec::new(),
+        }
+    }
+}
+
+impl KubernetesConfig {
+    /// Where a host path is on the shared workspace claim, if it is below
+    /// `workspace_claim_root`.
+    fn claim_sub_path(&self, host_path: &str) -> Option<String> {
+        self.workspace_claim.as_ref()?;
+        let relative = Path::new(host_path)
+            .strip_prefix(self.workspace_claim_root.as_ref()?)
+            .ok()?;
+        Some(relative.to_string_lossy().to_string())
+    }
+}
+
+/// A port forward; the listener stops when it is dropped.
+struct PortForward(JoinHandle<()>);
+
+impl Drop for PortForward {
+    fn drop(&mut self) {
+        self.0.abort();
+    }
+}
+
+/// Container runtime client for a Kubernetes cluster.
+#[derive(Clone)]
+pub struct KubernetesRuntime {
+    config: KubernetesConfig,
+    api: reqwest::Url,
+    namespace: String,
+    token_file: Option<PathBuf>,
+    http: reqwest::Client,
+    /// For exec websockets to an https API server.
+    tls: Option<TlsConnector>,
+    forwards: Arc<Mutex<HashMap<String, Vec<PortForward>>>>,
+}
+
+impl KubernetesRuntime {
+    /// Client for the configured cluster, falling back to the in-cluster
+    /// service account.
+    pub fn new(config: KubernetesConfig) -> ContainerResult<Self> {
+        let service_account = Path::new(SERVICE_ACCOUNT_DIR);
+        let api_server = match &config.api_server {
+            Some(url) => url.clone(),
+            None => {
+                let host = std::env::var("KUBERNETES_SERVICE_HOST").map_err(|_| {
+                    ContainerError::InvalidInput(
+                        "not running in a cluster; set container.kubernetes.api_server".to_string(),
+                    )
+                })?;
+                let port =
+                    std::env::var("KUBERNETES_SERVICE_PORT").unwrap_or_else(|_| "443".to_string());
+                // IPv6 service addresses need brackets in the URL.
+                if host.contains(':') {
+                    format!("https://[{host}]:{port}")
+                } else {
+                    format!("https://{host}:{port}")
+                }
+            }
+        };
+        let api = reqwest::Url::parse(&api_server).map_err(|e| {
+            ContainerError::InvalidInput(format!("invalid API server '{api_server}': {e}"))
+        })?;
+        let token_file = config
+            .token_file
+            .clone()
+            .or_else(|| Some(service_account.join("token")).filter(|p| p.exists()));
+        let ca_file = config
+            .ca_file
+            .clone()
+            .or_else(|| Some(service_account.join("ca.crt")).filter(|p| p.exists()));
+        let namespace = config
+            .namespace
+            .clone()
+            .or_else(|| {
+                let namespace = std::fs::read_to_string(service_account.join("namespace")).ok()?;
+                Some(namespace.trim().to_string()).filter(|n| !n.is_empty())
+            })
+            .unwrap_or_else(|| "default".to_string());
+
+        let mut http = reqwest::Client::builder().connect_timeout(Duration::from_secs(10));
+        let mut roots = RootCertStore::empty();
+        if let Some(path) = &ca_file {
+            let pem = std::fs::read(path)?;
+            for cert in reqwest::Certificate::from_pem_bundle(&pem).map_err(|e| {
+                ContainerError::InvalidInput(format!("invalid CA {}: {e}", path.display()))
+            })? {
+                http = http.add_root_certificate(cert);
+            }
+            for cert in CertificateDer::pem_slice_iter(&pem) {
+                let cert = cert.map_err(|e| {
+                    ContainerError::InvalidInput(format!("invalid CA {}: {e}", path.display()))
+                })?;
+                roots
+                    .add(cert)
+                    .map_err(|e| ContainerError::InvalidInput(e.to_string()))?;
+            }
+        } else {
+            roots.extend(webpki_roots::TLS_SERVER_ROOTS.iter().cloned());
+        }
+        let http = http
+            .build()
+            .map_err(|e| ContainerError::InvalidInput(e.to_string()))?;
+        let tls = if api.scheme() == "https" {
+            let provider = Arc::new(tokio_rustls::rustls::crypto::ring::default_provider());
+            let tls = ClientConfig::builder_with_provider(provider)
+                .with_safe_default_protocol_versions()
+                .map_err(|e| ContainerError::InvalidInput(e.to_string()))?
+                .with_root_certificates(roots)
+                .with_no_client_auth();
+            Some(TlsConnector::from(Arc::new(tls)))
+        } else {
+            None
+        };
+
+        Ok(Self {
+            config,
+            api,
+            namespace,
+            token_file,
+            http,
+            tls,
+            forwards: Arc::default(),
+        })
+    }
+
+    /// Namespace of session pods.
+    pub fn namespace(&self) -> &str {
+        &self.namespace
+    }
+
+    /// API server URL.
+    pub fn api_server(&self) -> &str {
+        self.api.as_str()
+    }
+
+    /// Check if the API server answers; returns its version information.
+    pub async fn health_check(&self) -> ContainerResult<String> {
+        let (status, body) = self.call("version", Method::GET, "/version", None).await?;
+        if !status.is_success() {
+            return Err(api_error("version", status, &body));
+        }
+        Ok(String::from_utf8_lossy(&body).to_string())
+    }
+
+    fn token(&self) -> Option<String> {
+        let token = std::fs::read_to_string(self.token_file.as_ref()?).ok()?;
+        Some(token.trim().to_string()).filter(|t| !t.is_empty())
+    }
+
+    fn path(&self, kind: &str, name: &str) -> String {
+        format!("/api/v1/namespaces/{}/{kind}/{name}", self.namespace)
+    }
+
+    /// Send a request with an optional JSON body; returns status and body.
+    async fn call(
+        &self,
+        command: &str,
+        method: Method,
+        path: &str,
+        body: Option<Value>,
+    ) -> ContainerResult<(StatusCode, Vec<u8>)> {
+        let url = format!("{}{}", self.api.as_str().trim_end_matches('/'), path);
+        let mut request = self.http.request(method, url);
+        if let Some(token) = self.token() {
+            request = request.bearer_auth(token);
+        }
+        if let Some(body) = body {
+            request = request.json(&body);
+        }
+        let response = request
+            .send()
+            .await
+            .map_err(|e| failed(command, format!("cannot reach {}: {e}", self.api)))?;
+        let status = response.status();
+        let body = response.bytes().await.map_err(|e| failed(command, e))?;
+        Ok((status, body.to_vec()))
+    }
+
+    /// GET an object; None when it does not exist.
+    async fn get(&self, command: &str, path: &str) -> ContainerResult<Option<Value>> {
+        let (status, body) = self.call(command, Method::GET, path, None).await?;
+        match status {
+            StatusCode::NOT_FOUND => Ok(None),
+            s if s.is_success() => serde_json::from_slice(&body)
+                .map(Some)
+                .map_err(|e| ContainerError::ParseError(e.to_string())),
+            _ => Err(api_error(command, status, &body)),
+        }
+    }
+
+    /// POST an object. An existing one is an error unless `exists_ok`.
+    async fn create(
+        &self,
+        command: &str,
+        kind: &str,
+        object: Value,
+        exists_ok: bool,
+    ) -> ContainerResult<()> {
+        let path = format!("/api/v1/namespaces/{}/{kind}", self.namespace);
+        let (status, body) = self
+            .call(command, Method::POST, &path, Some(object))
+            .await?;
+        match status {
+            s if s.is_success() => Ok(()),
+            StatusCode::CONFLICT if exists_ok => Ok(()),
+            _ => Err(api_error(command, status, &body)),
+        }
+    }
+
+    /// DELETE an object; a missing one is not an error.
+    async fn delete(&self, command: &str, path: &str) -> ContainerResult<()> {
+        let (status, body) = self.call(command, Method::DELETE, path, None).await?;
+        if status.is_success() || status == StatusCode::NOT_FOUND {
+            Ok(())
+        } else {
+            Err(api_error(command, status, &body))
+        }
+    }
+
+    /// The Service of a session, which exists from creation to removal.
+    async fn service(&self, command: &str, id: &str) -> ContainerResult<Value> {
+        validate_container_id_or_name(id)?;
+        self.get(command, &self.path("services", &object_name(id)))
+            .await?
+            .ok_or_else(|| ContainerError::ContainerNotFound(id.to_string()))
+    }
+
+    async fn pod(&self, command: &str, id: &str) -> ContainerResult<Option<Value>> {
+        validate_container_id_or_name(id)?;
+        self.get(command, &self.path("pods", &object_name(id)))
+            .await
+    }
+
+    /// Create the pod from the manifest kept on the Service.
+    async fn create_pod(&self, command: &str, service: &Value) -> ContainerResult<()> {
+        let pod = service
+            .pointer("/metadata/annotations")
+            .and_then(|a| a.get(POD_ANNOTATION))
+            .and_then(Value::as_str)
+            .and_then(|pod| serde_json::from_str::<Value>(pod).ok())
+            .ok_or_else(|| {
+                ContainerError::ParseError(format!("service has no {POD_ANNOTATION} annotation"))
+            })?;
+        self.create(command, "pods", pod, false).await
+    }
+
+    /// Delete the pod and wait until it is gone, so it can be created
+    /// again under the same name.
+    async fn delete_pod(&self, command: &str, name: &str, grace: u32) -> ContainerResult<()> {
+        let path = self.path("pods", name);
+        self.delete(command, &format!("{path}?gracePeriodSeconds={grace}"))
+            .await?;
+        let deadline = tokio::time::Instant::now() + Duration::from_secs(u64::from(grace) + 30);
+        while self.get(command, &path).await?.is_some() {
+            if tokio::time::Instant::now() > deadline {
+                return Err(failed(command, format!("pod {name} is still terminating")));
+            }
+            tokio::time::sleep(Duration::from_millis(500)).await;
+        }
+        Ok(())
+    }
+
+    /// Listen on the session's host ports and forward to its Service,
+    /// unless that already happens. Also restores the forwards of running
+    /// sessions after a backend restart.
+    async fn ensure_forwards(&self, name: &str, service: &Value) -> ContainerResult<()> {
+        let mut forwards = self.forwards.lock().await;
+        if forwards.contains_key(name) {
+            return Ok(());
+        }
+        let host = format!("{name}.{}.svc", self.namespace);
+        let mut tasks = Vec::new();
+        for port in service
+            .pointer("/spec/ports")
+            .and_then(Value::as_array)
+            .into_iter()
+            .flatten()
+        {
+            if port.get("protocol").and_then(Value::as_str) != Some("TCP") {
+                continue;
+            }
+            let Some(port) = port.get("port").and_then(Value::as_u64) else {
+                continue;
+            };
+            let port = port as u16;
+            let listener = TcpListener::bind(("127.0.0.1", port))
+                .await
+                .map_err(|e| failed("run", format!("cannot listen on port {port}: {e}")))?;
+            tasks.push(PortForward(tokio::spawn(forward_port(
+                listener,
+                host.clone(),
+                port,
+            ))));
+        }
+        forwards.insert(name.to_string(), tasks);
+        Ok(())
+    }
+
+    /// Run `command` in the session container over the exec websocket.
+    async fn exec(&self, container_id: &str, command: &[&str]) -> ContainerResult<ExecOutput> {
+        validate_container_id_or_name(container_id)?;
+
+        let mut path = format!(
+            "{}/exec?container={CONTAINER}&stdout=true&stderr=true",
+            self.path("pods", &object_name(container_id))
+        );
+        for arg in command {
+            path.push_str("&command=");
+            path.push_str(&urlencoding::encode(arg));
+        }
+        let host = self
+            .api
+            .host_str()
+            .ok_or_else(|| failed("exec", "API server URL has no host"))?
+            .to_string();
+        let port = self.api.port_or_known_default().unwrap_or(443);
+        let scheme = if self.tls.is_some() { "wss" } else { "ws" };
+        let mut request = format!("{scheme}://{host}:{port}{path}")
+            .into_client_request()
+            .map_err(|e| failed("exec", e))?;
+        let headers = request.headers_mut();
+        headers.insert(
+            "Sec-WebSocket-Protocol",
+            HeaderValue::from_static("v4.channel.k8s.io"),
+        );
+        if let Some(token) = self.token() {
+            let value =
+                HeaderValue::from_str(&format!("Bearer {token}")).map_err(|e| failed("exec", e))?;
+            headers.insert("Authorization", value);
+        }
+
+        let address = host
+            .trim_start_matches('[')
+            .trim_end_matches(']')
+            .to_string();
+        let stream = TcpStream::connect((address.as_str(), port))
+            .await
+            .map_err(|e| failed("exec", format!("cannot reach {}: {e}", self.api)))?;
+        match &self.tls {
+            Some(tls) => {
+                let server_name = ServerName::try_from(address).map_err(|e| failed("exec", e))?;
+                let stream = tls
+                    .connect(server_name, stream)
+                    .await
+                    .map_err(|e| failed("exec", e))?;
+                let (ws, _) = tokio_tungstenite::client_async(request, stream)
+                    .await
+                    .map_err(|e| failed("exec", e))?;
+                read_exec(ws).await
+            }
+            None => {
+                let (ws, _) = tokio_tungstenite::client_async(request, stream)
+                    .await
+                    .map_err(|e| failed("exec", e))?;
+                read_exec(ws).await
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl ContainerRuntimeApi for KubernetesRuntime {
+    async fn create_container(&self, config: &ContainerConfig) -> ContainerResult<String> {
+        config.validate()?;
+
+        let requested = config
+            .name
+            .clone()
+            .unwrap_or_else(|| format!("oqto-{}", nanoid::nanoid!(8, &NAME_ALPHABET)));
+        let name = object_name(&requested);
+        let objects = manifests(&self.config, &name, &requested, config);
+        if let Some(claim) = objects.claim {
+            self.create("run", "persistentvolumeclaims", claim, true)
+                .await?;
+        }
+        // A Service left over from a failed start is replaced.
+        self.delete("run", &self.path("services", &name)).await?;
+        self.create("run", "services", objects.service.clone(), false)
+            .await?;
+        self.create("run", "pods", objects.pod, false).await?;
+        self.ensure_forwards(&name, &objects.service).await?;
+        metrics().container_started();
+        Ok(name)
+    }
+
+    async fn stop_container(
+        &self,
+        container_id: &str,
+        timeout_seconds: Option<u32>,
+    ) -> ContainerResult<()> {
+        validate_container_id_or_name(container_id)?;
+
+        let name = object_name(container_id);
+        self.forwards.lock().await.remove(&name);
+        self.delete_pod("stop", &name, timeout_seconds.unwrap_or(10))
+            .await?;
+        metrics().container_stopped();
+        Ok(())
+    }
+
+    async fn start_container(&self, container_id: &str) -> ContainerResult<()> {
+        let service = self.service("start", container_id).await?;
+        let name = object_name(container_id);
+        let started = self
+            .pod("start", container_id)
+            .await?
+            .is_some_and(|pod| matches!(pod_status(&pod), "running" | "created"));
+        if !started {
+            // An exited pod cannot be restarted, only created again.
+            self.delete_pod("start", &name, 0).await?;
+            self.create_pod("start", &service).await?;
+        }
+        self.ensure_forwards(&name, &service).await?;
+        metrics().container_started();
+        Ok(())
+    }
+
+    async fn remove_container(&self, container_id: &str, _force: bool) -> ContainerResult<()> {
+        validate_container_id_or_name(container_id)?;
+
+        // The home claim is kept so a recreated session container finds its
+        // files; it carries the managed-by label for cleanup.
+        let name = object_name(container_id);
+        self.forwards.lock().await.remove(&name);
+        self.delete_pod("rm", &name, 0).await?;
+        self.delete("rm", &self.path("services", &name)).await
+    }
+
+    async fn list_containers(&self, all: bool) -> ContainerResult<Vec<Container>> {
+        let selector =
+            urlencoding::encode(&format!("{}={}", MANAGED_BY.0, MANAGED_BY.1)).into_owned();
+        let list = |kind: &str| {
+            format!(
+                "/api/v1/namespaces/{}/{kind}?labelSelector={selector}",
+                self.namespace
+            )
+        };
+        let services = self.get("ps", &list("services")).await?.unwrap_or_default();
+        let pods = self.get("ps", &list("pods")).await?.unwrap_or_default();
+        let pods: HashMap<&str, &Value> = pods
+            .get("items")
+            .and_then(Value::as_array)
+            .into_iter()
+            .flatten()
+            .filter_map(|pod| Some((pod.pointer("/metadata/name")?.as_str()?, pod)))
+            .collect();
+
+        let mut containers = Vec::new();
+        for service in services
+            .get("items")
+            .and_then(Value::as_array)
+            .into_iter()
+            .flatten()
+        {
+            let Some(name) = service.pointer("/metadata/name").and_then(Value::as_str) else {
+                continue;
+            };
+            let status = pods.get(name).map_or("exited", |pod| pod_status(pod));
+            if !all && status != "running" {
+                continue;
+            }
+            let label = |key: &str| {
+                service
+                    .pointer("/metadata/labels")
+                    .and_then(|labels| labels.get(key))
+                    .and_then(Value::as_str)
+                    .unwrap_or_default()
+                    .to_string()
+            };
+            let image = service
+                .pointer("/metadata/annotations")
+                .and_then(|a| a.get(POD_ANNOTATION))
+                .and_then(Value::as_str)
+                .and_then(|pod| serde_json::from_str::<Value>(pod).ok())
+                .and_then(|pod| {
+                    let image = pod.pointer("/spec/containers/0/image")?;
+                    image.as_str().map(str::to_string)
+                })
+                .unwrap_or_default();
+            let ports = service
+                .pointer("/spec/ports")
+                .and_then(Value::as_array)
+                .into_iter()
+                .flatten()
+                .map(|port| ContainerPort {
+                    host_ip: "127.0.0.1".to_string(),
+                    host_port: port.get("port").and_then(Value::as_u64).unwrap_or_default() as u16,
+                    container_port: port
+                        .get("targetPort")
+                        .and_then(Value::as_u64)
+                        .unwrap_or_default() as u16,
+                    protocol: port
+                        .get("protocol")
+                        .and_then(Value::as_str)
+                        .unwrap_or("TCP")
+                        .to_lowercase(),
+                })
+                .collect();
+            containers.push(Container {
+                id: name.to_string(),
+                names: vec![label(CONTAINER_LABEL)],
+                image,
+                state: serde_json::from_value(json!(status)).unwrap_or_default(),
+                status: status.to_string(),
+                created: service
+                    .pointer("/metadata/creationTimestamp")
+                    .and_then(Value::as_str)
+                    .unwrap_or_default()
+                    .to_string(),
+                ports,
+            });
+        }
+        Ok(containers)
+    }
+
+    async fn container_state_status(&self, id_or_name: &str) -> ContainerResult<Option<String>> {
+        let service = match self.service("inspect", id_or_name).await {
+            Ok(service) => service,
+            Err(ContainerError::ContainerNotFound(_)) => return Ok(None),
+            Err(e) => return Err(e),
+        };
+        let status = match self.pod("inspect", id_or_name).await? {
+            Some(pod) => pod_status(&pod),
+            // Stopped: the pod was deleted, the Service is kept.
+            None => "exited",
+        };
+        if matches!(status, "running" | "created") {
+            self.ensure_forwards(&object_name(id_or_name), &service)
+                .await?;
+        }
+        Ok(Some(status.to_string()))
+    }
+
+    async fn container_exit_state(
+        &self,
+        id_or_name: &str,
+    ) -> ContainerResult<Option<ContainerExitState>> {
+        let Some(pod) = self.pod("inspect", id_or_name).await? else {
+            return Ok(None);
+        };
+        let Some(status) = pod.pointer("/status/containerStatuses/0") else {
+            return Ok(None);
+        };
+        let Some(terminated) = status
+            .pointer("/state/terminated")
+            .or_else(|| status.pointer("/lastState/terminated"))
+        else {
+            return Ok(None);
+        };
+        let reason = terminated
+            .get("reason")
+            .and_then(Value::as_str)
+            .unwrap_or_default();
+        Ok(Some(ContainerExitState {
+            exit_code: terminated
+                .get("exitCode")
+                .and_then(Value::as_i64)
+                .unwrap_or_default() as i32,
+            oom_killed: reason == "OOMKilled",
+            memory_limit: memory_limit(&pod),
+            error: terminated
+                .get("message")
+                .and_then(Value::as_str)
+                .unwrap_or_default()
+                .trim()
+                .to_string(),
+        }))
+    }
+
+    async fn container_ip(&self, id_or_name: &str) -> ContainerResult<Option<IpAddr>> {
+        Ok(self
+            .pod("inspect", id_or_name)
+            .await?
+            .and_then(|pod| pod.pointer("/status/podIP")?.as_str()?.parse().ok()))
+    }
+
+    async fn tail_logs(&self, container_id: &str, lines: u32) -> ContainerResult<String> {
+        validate_container_id_or_name(container_id)?;
+
+        let path = format!(
+            "{}/log?container={CONTAINER}&tailLines={lines}",
+            self.path("pods", &object_name(container_id))
+        );
+        let (status, body) = self.call("logs", Method::GET, &path, None).await?;
+        match status {
+            s if s.is_success() => Ok(String::from_utf8_lossy(&body).to_string()),
+            StatusCode::NOT_FOUND => {
+                Err(ContainerError::ContainerNotFound(container_id.to_string()))
+            }
+            _ => Err(api_error("logs", status, &body)),
+        }
+    }
+
+    /// Nodes pull images themselves, so there is no local image to inspect;
+    /// the reference stands in for the digest.
+    async fn get_image_digest(&self, image: &str) -> ContainerResult<Option<String>> {
+        super::validate_image_name(image)?;
+        Ok(Some(image.to_string()))
+    }
+
+    /// Limits are changed in the stored manifest and apply from the next
+    /// start of the pod.
+    async fn update_resources(
+        &self,
+        container_id: &str,
+        limits: &ResourceLimits,
+    ) -> ContainerResult<()> {
+        limits.validate()?;
+
+        let service = self.service("update", container_id).await?;
+        let mut pod: Value = service
+            .pointer("/metadata/annotations")
+            .and_then(|a| a.get(POD_ANNOTATION))
+            .and_then(Value::as_str)
+            .and_then(|pod| serde_json::from_str(pod).ok())
+            .ok_or_else(|| {
+                ContainerError::ParseError(format!("service has no {POD_ANNOTATION} annotation"))
+            })?;
+        let current = pod
+            .pointer("/spec/containers/0/resources")
+            .cloned()
+            .unwrap_or_else(|| json!({}));
+        let mut resources = pod_resources(limits);
+        // GPUs only change when the container is recreated.
+        if let Some(gpus) = current.get("limits").and_then(|l| l.get("nvidia.com/gpu")) {
+            resources["limits"]["nvidia.com/gpu"] = gpus.clone();
+        }
+        pod["spec"]["containers"][0]["resources"] = resources;
+
+        let patch = json!({
+            "metadata": { "annotations": { POD_ANNOTATION: pod.to_string() } }
+        });
+        let path = self.path("services", &object_name(container_id));
+        let url = format!("{}{}", self.api.as_str().trim_end_matches('/'), path);
+        let mut request = self
+            .http
+            .patch(url)
+            .header("content-type", "application/merge-patch+json")
+            .body(patch.to_string());
+        if let Some(token) = self.token() {
+            request = request.bearer_auth(token);
+        }
+        let response = request
+            .send()
+            .await
+            .map_err(|e| failed("update", format!("cannot reach {}: {e}", self.api)))?;
+        let status = response.status();
+        if !status.is_success() {
+            let body = response.bytes().await.unwrap_or_default();
+            return Err(api_error("update", status, &body));
+        }
+        Ok(())
+    }
+
+    /// Usage from the metrics API (metrics-server must be installed).
+    async fn get_stats(&self, container_id: &str) -> ContainerResult<ContainerStats> {
+        validate_container_id_or_name(container_id)?;
+
+        let name = object_name(container_id);
+        let path = format!(
+            "/apis/metrics.k8s.io/v1beta1/namespaces/{}/pods/{name}",
+            self.namespace
+        );
+        let usage = self
+            .get("stats", &path)
+            .await?
+            .ok_or_else(|| ContainerError::ContainerNotFound(container_id.to_string()))?;
+        let pod = self.pod("stats", container_id).await?;
+        Ok(pod_stats(container_id, &usage, pod.as_ref()))
+    }
+
+    /// There is no detached exec; the command is started in the background
+    /// by a shell that returns right away.
+    async fn exec_detached(&self, container_id: &str, command: &[&str]) -> ContainerResult<()> {
+        let mut args = vec!["sh", "-c", "nohup \"$@\" >/dev/null 2>&1 &", "sh"];
+        args.extend(command);
+        self.exec_output(container_id, &args).await?;
+        Ok(())
+    }
+
+    async fn exec_output(&self, container_id: &str, command: &[&str]) -> ContainerResult<String> {
+        let output = self.exec(container_id, command).await?;
+        match exec_result(&output.status) {
+            Ok(0) => Ok(String::from_utf8_lossy(&output.stdout).to_string()),
+            Ok(_) => Err(ContainerError::CommandFailed {
+                command: "exec".to_string(),
+                message: String::from_utf8_lossy(&output.stderr).to_string(),
+            }),
+            Err(message) => Err(failed("exec", message)),
+        }
+    }
+}
+
+/// Lowercase letters and digits, for generated names.
+const NAME_ALPHABET: [char; 36] = [
+    'a', 'b', 'c', 'd', 'e', 'f', 'g', 'h', 'i', 'j', 'k', 'l', 'm', 'n', 'o', 'p', 'q', 'r', 's',
+    't', 'u', 'v', 'w', 'x', 'y', 'z', '0', '1', '2', '3', '4', '5', '6', '7', '8', '9',
+];
+
+/// Object name for a container ID or name: a DNS label (lowercase letters,
+/// digits and `-`, at most 63 characters).
+fn object_name(id: &str) -> String {
+    let mapped: String = id
+        .to_lowercase()
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '-' })
+        .take(63)
+        .collect();
+    mapped.trim_matches('-').to_string()
+}
+
+fn failed(command: &str, message: impl ToString) -> ContainerError {
+    ContainerError::CommandFailed {
+        command: command.to_string(),
+        message: message.to_string(),
+    }
+}
+
+/// The error of a failed call, with the `message` of the API's Status.
+fn api_error(command: &str, status: StatusCode, body: &[u8]) -> ContainerError {
+    let message = serde_json::from_slice::<Value>(body)
+        .ok()
+        .and_then(|v| v.get("message")?.as_str().map(str::to_string))
+        .unwrap_or_else(|| String::from_utf8_lossy(body).trim().to_string());
+    failed(command, format!("{message} (HTTP {})", status.as_u16()))
+}
+
+/// Container status of a pod, in the terms `inspect` uses.
+fn pod_status(pod: &Value) -> &'static str {
+    if pod.pointer("/metadata/deletionTimestamp").is_some() {
+        return "removing";
+    }
+    match pod.pointer("/status/phase").and_then(Value::as_str) {
+        Some("Running") => "running",
+        Some("Succeeded") | Some("Failed") => "exited",
+        Some("Pending") | None => "created",
+        Some(_) => "unknown",
+    }
+}
+
+/// The objects of a session container.
+struct Manifests {
+    pod: Value,
+    service: Value,
+    claim: Option<Value>,
+}
+
+fn manifests(
+    settings: &KubernetesConfig,
+    name: &str,
+    requested: &str,
+    config: &ContainerConfig,
+) -> Manifests {
+    let container_label: String = requested
+        .chars()
+        .filter(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'))
+        .take(63)
+        .collect();
+    let labels = json!({
+        MANAGED_BY.0: MANAGED_BY.1,
+        POD_LABEL: name,
+        CONTAINER_LABEL: container_label,
+    });
+
+    let mut volumes = Vec::new();
+    let mut mounts = Vec::new();
+    let mut uses_claim = false;
+    let mut uses_home = false;
+    let all_volumes = config
+        .volumes
+        .iter()
+        .map(|(host, container)| (host, container, false))
+        .chain(
+            config
+                .read_only_volumes
+                .iter()
+                .map(|(host, container)| (host, container, true)),
+        );
+    for (i, (host, container, read_only)) in all_volumes.enumerate() {
+        let mut mount = json!({ "mountPath": container, "readOnly": read_only });
+        if let Some(sub_path) = settings.claim_sub_path(host) {
+            uses_claim = true;
+            mount["name"] = json!("workspace");
+            if !sub_path.is_empty() {
+                mount["subPath"] = json!(sub_path);
+            }
+        } else if !read_only {
+            uses_home = true;
+            mount["name"] = json!("home");
+            let sub_path = container.trim_matches('/').replace('/', "-");
+            mount["subPath"] = json!(if sub_path.is_empty() {
+                "root".to_string()
+            } else {
+                sub_path
+            });
+        } else {
+            // Read-only mounts (shared configs) must exist on every node.
+            let volume = format!("host-{i}");
+            volumes.push(json!({ "name": volume, "hostPath": { "path": host } }));
+            mount["name"] = json!(volume);
+        }
+        mounts.push(mount);
+    }
+    let claim_name = format!("{name}-home");
+    if let Some(claim) = settings.workspace_claim.as_ref().filter(|_| uses_claim) {
+        volumes.push(json!({
+            "name": "workspace",
+            "persistentVolumeClaim": { "claimName": claim },
+        }));
+    }
+    let claim = uses_home.then(|| {
+        volumes.push(json!({
+            "name": "home",
+            "persistentVolumeClaim": { "claimName": claim_name },
+        }));
+        let mut claim = json!({
+            "apiVersion": "v1",
+            "kind": "PersistentVolumeClaim",
+            "metadata": { "name": claim_name, "labels": labels },
+            "spec": {
+                "accessModes": ["ReadWriteOnce"],
+                "resources": { "requests": { "storage": settings.volume_size } },
+            },
+        });
+        if let Some(class) = &settings.storage_class {
+            claim["spec"]["storageClassName"] = json!(class);
+        }
+        claim
+    });
+
+    let mut env: Vec<Value> = config
+        .env
+        .iter()
+        .map(|(key, value)| json!({ "name": key, "value": value }))
+        .collect();
+    env.sort_by(|a, b| a["name"].as_str().cmp(&b["name"].as_str()));
+    let mut container = json!({
+        "name": CONTAINER,
+        "image": config.image,
+        "env": env,
+        "ports": config
+            .ports
+            .iter()
+            .map(|port| json!({
+                "containerPort": port.container_port,
+                "protocol": port.protocol.to_uppercase(),
+            }))
+            .collect::<Vec<_>>(),
+        "volumeMounts": mounts,
+        "resources": pod_resources(&config.resources),
+    });
+    if !config.command.is_empty() {
+        container["args"] = json!(config.command);
+    }
+    if let Some(ref workdir) = config.workdir {
+        container["workingDir"] = json!(workdir);
+    }
+    let devices = config.resources.gpu_devices();
+    if !devices.is_empty() {
+        // Pods get a number of GPUs, not specific devices; "all" is one.
+        let count = if devices == ["all"] { 1 } else { devices.len() };
+        container["resources"]["limits"]["nvidia.com/gpu"] = json!(count.to_string());
+    }
+
+    let mut spec = json!({
+        "restartPolicy": "Never",
+        "containers": [container],
+        "volumes": volumes,
+        "automountServiceAccountToken": settings.service_account.is_some(),
+    });
+    if let Some(hostname) = &config.hostname {
+        spec["hostname"] = json!(object_name(hostname));
+    }
+    if !settings.node_selector.is_empty() {
+        spec["nodeSelector"] = json!(settings.node_selector);
+    }
+    if let Some(account) = &settings.service_account {
+        spec["serviceAccountName"] = json!(account);
+    }
+    if !settings.image_pull_secrets.is_empty() {
+        spec["imagePullSecrets"] = settings
+            .image_pull_secrets
+            .iter()
+            .map(|secret| json!({ "name": secret }))
+            .collect();
+    }
+    let pod = json!({
+        "apiVersion": "v1",
+        "kind": "Pod",
+        "metadata": { "name": name, "labels": labels, "annotations": config.labels },
+        "spec": spec,
+    });
+
+    let ports: Vec<Value> = config
+        .ports
+        .iter()
+        .map(|port| {
+            json!({
+                "name": format!("{}-{}", port.protocol.to_lowercase(), port.host_port),
+                "port": port.host_port,
+                "targetPort": port.container_port,
+                "protocol": port.protocol.to_uppercase(),
+            })
+        })
+        .collect();
+    let mut service_spec = json!({ "selector": { POD_LABEL: name }, "ports": ports });
+    if ports.is_empty() {
+        // A Service without ports must be headless.
+        service_spec["clusterIP"] = json!("None");
+    }
+    let service = json!({
+        "apiVersion": "v1",
+        "kind": "Service",
+        "metadata": {
+            "name": name,
+            "labels": labels,
+            "annotations": { POD_ANNOTATION: pod.to_string() },
+        },
+        "spec": service_spec,
+    });
+
+    Manifests {
+        pod,
+        service,
+        claim,
+    }
+}
+
+/// Container `resources` for `limits`: CPU shares become a CPU request
+/// (1024 shares = one core), memory a request and limit.
+fn pod_resources(limits: &ResourceLimits) -> Value {
+    let mut resources = json!({ "requests": {}, "limits": {} });
+    if let Some(shares) = limits.cpu_shares {
+        let millis = (u64::from(shares) * 1000 / 1024).max(1);
+        resources["requests"]["cpu"] = json!(format!("{millis}m"));
+    }
+    if let Some(memory) = limits.memory_mb {
+        resources["requests"]["memory"] = json!(format!("{memory}Mi"));
+        resources["limits"]["memory"] = json!(format!("{memory}Mi"));
+    }
+    resources
+}
+
+fn memory_limit(pod: &Value) -> Option<u64> {
+    pod.pointer("/spec/containers/0/resources/limits/memory")
+        .and_then(Value::as_str)
+        .and_then(parse_quantity)
+        .map(|bytes| bytes as u64)
+        .filter(|bytes| *bytes > 0)
+}
+
+/// A Kubernetes quantity (`250m`, `1500000n`, `512Mi`, `2G`, `3`) as a number.
+fn parse_quantity(quantity: &str) -> Option<f64> {
+    let quantity = quantity.trim();
+    let split = quantity
+        .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+        .unwrap_or(quantity.len());
+    let (number, suffix) = quantity.split_at(split);
+    let number: f64 = number.parse().ok()?;
+    let factor = match suffix {
+        "" => 1.0,
+        "n" => 1e-9,
+        "u" => 1e-6,
+        "m" => 1e-3,
+        "k" => 1e3,
+        "M" => 1e6,
+        "G" => 1e9,
+        "T" => 1e12,
+        "Ki" => 1024.0,
+        "Mi" => 1024.0 * 1024.0,
+        "Gi" => 1024.0 * 1024.0 * 1024.0,
+        "Ti" => 1024.0 * 1024.0 * 1024.0 * 1024.0,
+        _ => return None,
+    };
+    Some(number * factor)
+}
+
+/// Stats from a `PodMetrics` object, in the form `docker stats` prints
+/// them. Network and block I/O are not in the metrics API.
+fn pod_stats(container_id: &str, metrics: &Value, pod: Option<&Value>) -> ContainerStats {
+    let usage = metrics
+        .get("containers")
+        .and_then(Value::as_array)
+        .and_then(|containers| {
+            containers
+                .iter()
+                .find(|c| c.get("name").and_then(Value::as_str) == Some(CONTAINER))
+        })
+        .and_then(|c| c.get("usage"));
+    let quantity = |key: &str| {
+        usage
+            .and_then(|u| u.get(key))
+            .and_then(Value::as_str)
+            .and_then(parse_quantity)
+            .unwrap_or_default()
+    };
+    let cores = quantity("cpu");
+    let used = quantity("memory") as u64;
+    let limit = pod.and_then(memory_limit);
+    let (mem_usage, mem_percent) = match limit {
+        Some(limit) => (
+            format!("{} / {}", format_size(used), format_size(limit)),
+            used as f64 / limit as f64 * 100.0,
+        ),
+        None => (format_size(used), 0.0),
+    };
+    ContainerStats {
+        container_id: container_id.to_string(),
+        name: metrics
+            .pointer("/metadata/name")
+            .and_then(Value::as_str)
+            .unwrap_or_default()
+            .to_string(),
+        cpu_percent: format!("{:.2}%", cores * 100.0),
+        mem_usage,
+        mem_percent: format!("{mem_percent:.2}%"),
+        net_io: String::new(),
+        block_io: String::new(),
+        pids: String::new(),
+    }
+}
+
+/// Accept connections on `listener` and pipe each to `host:port`.
+async fn forward_port(listener: TcpListener, host: String, port: u16) {
+    loop {
+        let mut inbound = match listener.accept().await {
+            Ok((inbound, _)) => inbound,
+            Err(e) => {
+                log::warn!("Accepting on forwarded port {port} failed: {e}");
+                tokio::time::sleep(Duration::from_millis(200)).await;
+                continue;
+            }
+        };
+        let host = host.clone();
+        tokio::spawn(async move {
+            match TcpStream::connect((host.as_str(), port)).await {
+                Ok(mut outbound) => {
+                    let _ = tokio::io::copy_bidirectional(&mut inbound, &mut outbound).await;
+                }
+                Err(e) => log::debug!("Forwarding port {port} to {host} failed: {e}"),
+            }
+        });
+    }
+}
+
+/// Output of an exec: the stdout, stderr and status channels.
+#[derive(Debug, Default)]
+struct ExecOutput {
+    stdout: Vec<u8>,
+    stderr: Vec<u8>,
+    status: Vec<u8>,
+}
+
+/// Read an exec websocket (`v4.channel.k8s.io`) until it closes. Each
+/// message starts with its channel: 1 stdout, 2 stderr, 3 the final status.
+async fn read_exec<S>(mut ws: WebSocketStream<S>) -> ContainerResult<ExecOutput>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    let mut output = ExecOutput::default();
+    while let Some(message) = ws.next().await {
+        match message.map_err(|e| failed("exec", e))? {
+            Message::Binary(data) if !data.is_empty() => match data[0] {
+                1 => output.stdout.extend_from_slice(&data[1..]),
+                2 => output.stderr.extend_from_slice(&data[1..]),
+                3 => output.status.extend_from_slice(&data[1..]),
+                _ => {}
+            },
+            Message::Close(_) => break,
+            _ => {}
+        }
+    }
+    Ok(output)
+}
+
+/// Exit code from the status channel of an exec, or why it did not run.
+fn exec_result(status: &[u8]) -> Result<i32, String> {
+    let Ok(status) = serde_json::from_slice::<Value>(status) else {
+        return Err("exec ended without a status".to_string());
+    };
+    if status.get("status").and_then(Value::as_str) == Some("Success") {
+        return Ok(0);
+    }
+    let exit_code = status
+        .pointer("/details/causes")
+        .and_then(Value::as_array)
+        .into_iter()
+        .flatten()
+        .find(|cause| cause.get("reason").and_then(Value::as_str) == Some("ExitCode"))
+        .and_then(|cause| cause.get("message")?.as_str()?.parse().ok());
+    exit_code.ok_or_else(|| {
+        status
+            .get("message")
+            .and_then(Value::as_str)
+            .unwrap_or("exec failed")
+            .to_string()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn session_containers_become_pods_services_and_claims() {
+        let settings = KubernetesConfig {
+            workspace_claim: Some("oqto-users".to_string()),
+            workspace_claim_root: Some(PathBuf::from("/srv/oqto/users")),
+            ..Default::default()
+        };
+        let config = ContainerConfig::new("oqto:latest")
+            .name("oqto-ses_AB12")
+            .hostname("oqto-ses_AB12")
+            .port(41830, 41820)
+            .volume("/srv/oqto/users/alice/ws", "/home/dev")
+            .volume("/var/lib/oqto/cache", "/home/dev/.cache")
+            .volume_ro("/etc/oqto/skel", "/opt/skel")
+            .resources(ResourceLimits {
+                cpu_shares: Some(512),
+                memory_mb: Some(1024),
+                ..Default::default()
+            });
+        let name = object_name("oqto-ses_AB12");
+        assert_eq!(name, "oqto-ses-ab12");
+
+        let objects = manifests(&settings, &name, "oqto-ses_AB12", &config);
+        let pod = &objects.pod;
+        let mounts = &pod["spec"]["containers"][0]["volumeMounts"];
+        assert_eq!(mounts[0]["name"], "workspace");
+        assert_eq!(mounts[0]["subPath"], "alice/ws");
+        assert_eq!(mounts[1]["name"], "home");
+        assert_eq!(mounts[1]["subPath"], "home-dev-.cache");
+        assert_eq!(mounts[2]["readOnly"], true);
+        assert_eq!(
+            pod["spec"]["volumes"][0]["hostPath"]["path"],
+            "/etc/oqto/skel"
+        );
+        assert_eq!(pod["spec"]["hostname"], "oqto-ses-ab12");
+        assert_eq!(pod["spec"]["automountServiceAccountToken"], false);
+        assert_eq!(
+            pod["spec"]["containers"][0]["resources"],
+            json!({
+                "requests": { "cpu": "500m", "memory": "1024Mi" },
+                "limits": { "memory": "1024Mi" },
+            })
+        );
+        assert_eq!(pod["metadata"]["labels"][CONTAINER_LABEL], "oqto-ses_AB12");
+
+        let claim = objects.claim.unwrap();
+        assert_eq!(claim["metadata"]["name"], "oqto-ses-ab12-home");
+        assert_eq!(claim["spec"]["resources"]["requests"]["storage"], "10Gi");
+
+        let service = &objects.service;
+        assert_eq!(service["spec"]["selector"][POD_LABEL], "oqto-ses-ab12");
+        assert_eq!(service["spec"]["ports"][0]["port"], 41830);
+        assert_eq!(service["spec"]["ports"][0]["targetPort"], 41820);
+        let stored: Value = serde_json::from_str(
+            service["metadata"]["annotations"][POD_ANNOTATION]
+                .as_str()
+                .unwrap(),
+        )
+        .unwrap();
+        assert_eq!(&stored, pod);
+    }
+
+    #[test]
+    fn metrics_and_exec_status_are_read() {
+        assert_eq!(parse_quantity("250m"), Some(0.25));
+        assert_eq!(parse_quantity("512Mi"), Some(512.0 * 1024.0 * 1024.0));
+        assert_eq!(parse_quantity("12x"), None);
+
+        let metrics = json!({
+            "metadata": { "name": "oqto-ses-1" },
+            "containers": [{
+                "name": "session",
+                "usage": { "cpu": "1500000000n", "memory": "262144Ki" }
+            }]
+        });
+        let pod = json!({
+            "spec": { "containers": [{ "resources": { "limits": { "memory": "1Gi" } } }] }
+        });
+        let stats = pod_stats("oqto-ses-1", &metrics, Some(&pod));
+        assert_eq!(stats.cpu_percent, "150.00%");
+        assert_eq!(stats.mem_usage, "256.00MiB / 1.00GiB");
+        assert_eq!(stats.mem_percent, "25.00%");
+
+        assert_eq!(exec_result(br#"{"status":"Success"}"#), Ok(0));
+        assert_eq!(
+            exec_result(
+                br#"{"status":"Failure","details":{"causes":[{"reason":"ExitCode","message":"2"}]}}"#
+            ),
+            Ok(2)
+        );
+        assert!(exec_result(br#"{"status":"Failure","message":"container not found"}"#).is_err());
+    }
+}
@@ -1,23 +1,37 @@
 //! Container runtime management module.
 //!
-//! Provides an async interface to manage containers via Docker or Podman CLI.
-//! The runtime is auto-detected or can be configured explicitly.
+//! Provides an async interface to manage containers via the Docker or Podman
+//! CLI, or via their REST API socket ([`SocketRuntime`]). The runtime is
+//! auto-detected or can be configured explicitly. [`KubernetesRuntime`] runs
+//! session containers as pods on a cluster instead.
 
 mod container;
 mod error;
+mod kubernetes;
+mod socket;
 
 #[allow(unused_imports)]
 pub use container::PortMapping;
-pub use container::{Container, ContainerConfig, ContainerStats};
+pub use container::{
+    Container, ContainerConfig, ContainerExitState, ContainerStats, ResourceLimits,
+};
 pub use error::{ContainerError, ContainerResult};
+pub use kubernetes::{KubernetesConfig, KubernetesRuntime};
+pub use socket::{SocketRuntime, default_socket_path};
 
 // Re-export validation function for use in this module
 use container::validate_image_name;
 
 use async_trait::async_trait;
 use serde::{Deserialize, Serialize};
+use std::net::IpAddr;
+use std::path::PathBuf;
 use std::process::Stdio;
+use tokio::io::{AsyncBufReadExt, AsyncRead, BufReader};
 use tokio::process::Command;
+use tokio::sync::mpsc;
+
+use crate::observability::metrics::metrics;
 
 /// Container runtime type.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
@@ -57,6 +71,19 @@ impl std::fmt::Display for RuntimeType {
     }
 }
 
+/// How the container runtime is driven, or where session containers run.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RuntimeApi {
+    /// Run the docker/podman binary.
+    #[default]
+    Cli,
+    /// Call the Docker-compatible REST API on a unix socket.
+    Socket,
+    /// Run a pod per session on a Kubernetes cluster.
+    Kubernetes,
+}
+
 /// Validate a container ID or name.
 ///
 /// Container IDs are hex strings (12 or 64 chars for docker/podman).
@@ -86,6 +113,79 @@ fn validate_container_id_or_name(id: &str) -> ContainerResult<()> {
     Ok(())
 }
 
+/// `run`/`update` flags applying `limits`. GPUs can only be passed through
+/// when a container is created, so `update` leaves them out.
+fn resource_args(runtime_type: RuntimeType, limits: &ResourceLimits, gpus: bool) -> Vec<String> {
+    let mut args = Vec::new();
+    if let Some(shares) = limits.cpu_shares {
+        args.push("--cpu-shares".to_string());
+        args.push(shares.to_string());
+    }
+    if let Some(memory) = limits.memory_mb {
+        // Equal swap limit: the memory limit covers swap too.
+        args.push("--memory".to_string());
+        args.push(format!("{memory}m"));
+        args.push("--memory-swap".to_string());
+        args.push(format!("{memory}m"));
+    }
+    if let Some(pids) = limits.pids_limit {
+        args.push("--pids-limit".to_string());
+        args.push(pids.to_string());
+    }
+    let devices = limits.gpu_devices();
+    if gpus && !devices.is_empty() {
+        match runtime_type {
+            RuntimeType::Docker => {
+                args.push("--gpus".to_string());
+                if devices == ["all"] {
+                    args.push("all".to_string());
+                } else {
+                    // Quoted so docker does not split the device list.
+                    args.push(format!("\"device={}\"", devices.join(",")));
+                }
+            }
+            // Podman passes GPUs through as CDI devices.
+            RuntimeType::Podman => {
+                for device in devices {
+                    args.push("--device".to_string());
+                    args.push(format!("nvidia.com/gpu={device}"));
+                }
+            }
+        }
+    }
+    args
+}
+
+/// Arguments checkpointing a container. Docker keeps named checkpoints;
+/// podman keeps one per container. Podman also checkpoints established TCP
+/// connections (open terminals), which CRIU otherwise refuses.
+fn checkpoint_args(runtime_type: RuntimeType, container_id: &str, name: &str) -> Vec<String> {
+    let args: &[&str] = match runtime_type {
+        RuntimeType::Docker => &["checkpoint", "create", container_id, name],
+        RuntimeType::Podman => &["container", "checkpoint", "--tcp-established", container_id],
+    };
+    args.iter().map(|arg| arg.to_string()).collect()
+}
+
+/// Arguments starting a container from the checkpoint `checkpoint_args` took.
+fn restore_args(runtime_type: RuntimeType, container_id: &str, name: &str) -> Vec<String> {
+    let args: &[&str] = match runtime_type {
+        RuntimeType::Docker => &["start", "--checkpoint", name, container_id],
+        RuntimeType::Podman => &["container", "restore", "--tcp-established", container_id],
+    };
+    args.iter().map(|arg| arg.to_string()).collect()
+}
+
+/// An image to build with `ContainerRuntimeApi::build_image`.
+#[derive(Debug, Clone)]
+pub struct ImageBuildSpec {
+    /// Build context directory.
+    pub context: PathBuf,
+    pub dockerfile: PathBuf,
+    pub tag: String,
+    pub labels: Vec<(String, String)>,
+}
+
 /// Container runtime client for managing containers.
 ///
 /// Supports both Docker and Podman with automatic detection.
@@ -107,10 +207,100 @@ pub trait ContainerRuntimeApi: Send + Sync {
         timeout_seconds: Option<u32>,
     ) -> ContainerResult<()>;
     async fn start_container(&self, container_id: &str) -> ContainerResult<()>;
+
+    /// Create a container without starting it.
+    async fn create_stopped_container(&self, _config: &ContainerConfig) -> ContainerResult<String> {
+        Err(ContainerError::CommandFailed {
+            command: "create".to_string(),
+            message: "creating containers without starting them is not supported".to_string(),
+        })
+    }
+
+    /// Set up the namespaces of a created or stopped container without
+    /// running its entrypoint, so rules can be attached to its network before
+    /// anything in it runs; `start_container` then runs it. Only Podman can
+    /// hold a container this way.
+    async fn hold_container(&self, _container_id: &str) -> ContainerResult<()> {
+        Err(ContainerError::CommandFailed {
+            command: "init".to_string(),
+            message: "holding containers before their start is not supported".to_string(),
+        })
+    }
+
+    /// Checkpoint the processes of a running container to disk (CRIU) and
+    /// stop it. Runtimes without checkpoint support fail.
+    async fn checkpoint_container(&self, _container_id: &str, _name: &str) -> ContainerResult<()> {
+        Err(ContainerError::CommandFailed {
+            command: "checkpoint".to_string(),
+            message: "checkpoints are not supported".to_string(),
+        })
+    }
+
+    /// Start a container from the checkpoint `name`.
+    async fn restore_container(&self, _container_id: &str, _name: &str) -> ContainerResult<()> {
+        Err(ContainerError::CommandFailed {
+            command: "restore".to_string(),
+            message: "checkpoints are not supported".to_string(),
+        })
+    }
+
     async fn remove_container(&self, container_id: &str, force: bool) -> ContainerResult<()>;
     async fn list_containers(&self, all: bool) -> ContainerResult<Vec<Container>>;
     async fn container_state_status(&self, id_or_name: &str) -> ContainerResult<Option<String>>;
+
+    /// How a stopped container exited, for diagnostics.
+    async fn container_exit_state(
+        &self,
+        _id_or_name: &str,
+    ) -> ContainerResult<Option<ContainerExitState>> {
+        Ok(None)
+    }
+
+    /// Address of a running container on its network, if it has one.
+    async fn container_ip(&self, _id_or_name: &str) -> ContainerResult<Option<IpAddr>> {
+        Ok(None)
+    }
+
+    /// Host pid of the main process of a running container, if the runtime
+    /// runs it on this host.
+    async fn container_pid(&self, _id_or_name: &str) -> ContainerResult<Option<u32>> {
+        Ok(None)
+    }
+
+    /// Last lines of the container's output.
+    async fn tail_logs(&self, _container_id: &str, _lines: u32) -> ContainerResult<String> {
+        Ok(String::new())
+    }
+
     async fn get_image_digest(&self, image: &str) -> ContainerResult<Option<String>>;
+
+    /// Whether the image is available locally.
+    async fn image_exists(&self, image: &str) -> ContainerResult<bool> {
+        Ok(self.get_image_digest(image).await?.is_some())
+    }
+
+    /// Build an image, sending each line of build output to `output` as it
+    /// comes.
+    async fn build_image(
+        &self,
+        _spec: &ImageBuildSpec,
+        _output: mpsc::Sender<String>,
+    ) -> ContainerResult<()> {
+        Err(ContainerError::CommandFailed {
+            command: "build".to_string(),
+            message: "image builds are not supported".to_string(),
+        })
+    }
+
+    /// Change the CPU, memory and process limits of a running container.
+    async fn update_resources(
+        &self,
+        _container_id: &str,
+        _limits: &ResourceLimits,
+    ) -> ContainerResult<()> {
+        Ok(())
+    }
+
     async fn get_stats(&self, container_id: &str) -> ContainerResult<ContainerStats>;
 
     /// Execute a command in a container (detached, fire-and-forget).
@@ -123,7 +313,9 @@ pub trait ContainerRuntimeApi: Send + Sync {
 #[async_trait]
 impl ContainerRuntimeApi for ContainerRuntime {
     async fn create_container(&self, config: &ContainerConfig) -> ContainerResult<String> {
-        self.create_container(config).await
+        let id = self.create_container(config).await?;
+        metrics().container_started();
+        Ok(id)
     }
 
     async fn stop_container(
@@ -131,11 +323,35 @@ impl ContainerRuntimeApi for ContainerRuntime {
         container_id: &str,
         timeout_seconds: Option<u32>,
     ) -> ContainerResult<()> {
-        self.stop_container(container_id, timeout_seconds).await
+        self.stop_container(container_id, timeout_seconds).await?;
+        metrics().container_stopped();
+        Ok(())
     }
 
     async fn start_container(&self, container_id: &str) -> ContainerResult<()> {
-        self.start_container(container_id).await
+        self.start_container(container_id).await?;
+        metrics().container_started();
+        Ok(())
+    }
+
+    async fn create_stopped_container(&self, config: &ContainerConfig) -> ContainerResult<String> {
+        self.create_stopped_container(config).await
+    }
+
+    async fn hold_container(&self, container_id: &str) -> ContainerResult<()> {
+        self.hold_container(container_id).await
+    }
+
+    async fn checkpoint_container(&self, container_id: &str, name: &str) -> ContainerResult<()> {
+        self.checkpoint_container(container_id, name).await?;
+        metrics().container_stopped();
+        Ok(())
+    }
+
+    async fn restore_container(&self, container_id: &str, name: &str) -> ContainerResult<()> {
+        self.restore_container(container_id, name).await?;
+        metrics().container_started();
+        Ok(())
     }
 
     async fn remove_container(&self, container_id: &str, force: bool) -> ContainerResult<()> {
@@ -150,10 +366,49 @@ impl ContainerRuntimeApi for ContainerRuntime {
         self.container_state_status(id_or_name).await
     }
 
+    async fn container_exit_state(
+        &self,
+        id_or_name: &str,
+    ) -> ContainerResult<Option<ContainerExitState>> {
+        self.container_exit_state(id_or_name).await
+    }
+
+    async fn container_ip(&self, id_or_name: &str) -> ContainerResult<Option<IpAddr>> {
+        self.container_ip(id_or_name).await
+    }
+
+    async fn container_pid(&self, id_or_name: &str) -> ContainerResult<Option<u32>> {
+        self.container_pid(id_or_name).await
+    }
+
+    async fn tail_logs(&self, container_id: &str, lines: u32) -> ContainerResult<String> {
+        self.get_logs(container_id, Some(lines)).await
+    }
+
     async fn get_image_digest(&self, image: &str) -> ContainerResult<Option<String>> {
         self.get_image_digest(image).await
     }
 
+    async fn image_exists(&self, image: &str) -> ContainerResult<bool> {
+        self.image_exists(image).await
+    }
+
+    async fn build_image(
+        &self,
+        spec: &ImageBuildSpec,
+        output: mpsc::Sender<String>,
+    ) -> ContainerResult<()> {
+        self.build_image(spec, output).await
+    }
+
+    async fn update_resources(
+        &self,
+        container_id: &str,
+        limits: &ResourceLimits,
+    ) -> ContainerResult<()> {
+        self.update_resources(container_id, limits).await
+    }
+
     async fn get_stats(&self, container_id: &str) -> ContainerResult<ContainerStats> {
         self.get_stats(container_id).await
     }
@@ -167,6 +422,22 @@ impl ContainerRuntimeApi for ContainerRuntime {
     }
 }
 
+/// Send the lines of `reader` to `output`. Returns the last non-blank one.
+async fn forward_lines(
+    reader: Option<impl AsyncRead + Unpin>,
+    output: mpsc::Sender<String>,
+) -> Option<String> {
+    let mut lines = BufReader::new(reader?).lines();
+    let mut last = None;
+    while let Ok(Some(line)) = lines.next_line().await {
+        if !line.trim().is_empty() {
+            last = Some(line.clone());
+        }
+        let _ = output.send(line).await;
+    }
+    last
+}
+
 impl Default for ContainerRuntime {
     fn default() -> Self {
         Self::new()
@@ -272,13 +543,29 @@ impl ContainerRuntime {
     /// The configuration is validated before creating the container to prevent
     /// injection attacks and ensure all inputs are well-formed.
     pub async fn create_container(&self, config: &ContainerConfig) -> ContainerResult<String> {
+        self.new_container("run", &["run", "-d"], config).await
+    }
+
+    /// Create a container without starting it.
+    pub async fn create_stopped_container(
+        &self,
+        config: &ContainerConfig,
+    ) -> ContainerResult<String> {
+        self.new_container("create", &["create"], config).await
+    }
+
+    /// Create a container from `config` with `subcommand` (`run -d` or
+    /// `create`); returns its ID.
+    async fn new_container(
+        &self,
+        command: &str,
+        subcommand: &[&str],
+        config: &ContainerConfig,
+    ) -> ContainerResult<String> {
         // Validate all inputs before creating the container
         config.validate()?;
 
-        let mut owned_args: Vec<String> = Vec::new();
-
-        owned_args.push("run".to_string());
-        owned_args.push("-d".to_string());
+        let mut owned_args: Vec<String> = subcommand.iter().map(|arg| arg.to_string()).collect();
 
         // Container name
         if let Some(ref name) = config.name {
@@ -324,6 +611,14 @@ impl ContainerRuntime {
                 owned_args.push(format!("{}:{}", host, container));
             }
         }
+        for (host, container) in &config.read_only_volumes {
+            owned_args.push("-v".to_string());
+            if self.runtime_type.needs_selinux_labels() {
+                owned_args.push(format!("{}:{}:ro,Z", host, container));
+            } else {
+                owned_args.push(format!("{}:{}:ro", host, container));
+            }
+        }
 
         // Environment variables
         for (key, value) in &config.env {
@@ -337,6 +632,9 @@ impl ContainerRuntime {
             owned_args.push(workdir.clone());
         }
 
+        // Resource limits
+        owned_args.extend(resource_args(self.runtime_type, &config.resources, true));
+
         // Image
         owned_args.push(config.image.clone());
 
@@ -352,14 +650,14 @@ impl ContainerRuntime {
             .output()
             .await
             .map_err(|e| ContainerError::CommandFailed {
-                command: "run".to_string(),
+                command: command.to_string(),
                 message: e.to_string(),
             })?;
 
         if !output.status.success() {
             let stderr = String::from_utf8_lossy(&output.stderr);
             return Err(ContainerError::CommandFailed {
-                command: "run".to_string(),
+                command: command.to_string(),
                 message: stderr.to_string(),
             });
         }
@@ -433,6 +731,123 @@ impl ContainerRuntime {
         Ok(())
     }
 
+    /// Set up the namespaces of a created or stopped container without
+    /// running its entrypoint (`podman init`); `start_container` runs it.
+    pub async fn hold_container(&self, container_id: &str) -> ContainerResult<()> {
+        validate_container_id_or_name(container_id)?;
+
+        if self.runtime_type != RuntimeType::Podman {
+            return Err(ContainerError::CommandFailed {
+                command: "init".to_string(),
+                message: "only Podman can hold a container before its start".to_string(),
+            });
+        }
+        self.run_checked("init", &["init".to_string(), container_id.to_string()])
+            .await
+    }
+
+    /// Checkpoint a running container's processes and stop it.
+    pub async fn checkpoint_container(
+        &self,
+        container_id: &str,
+        name: &str,
+    ) -> ContainerResult<()> {
+        validate_container_id_or_name(container_id)?;
+        validate_container_id_or_name(name)?;
+
+        self.run_checked(
+            "checkpoint",
+            &checkpoint_args(self.runtime_type, container_id, name),
+        )
+        .await
+    }
+
+    /// Start a container from a checkpoint. Docker's checkpoint is removed
+    /// once the container runs again.
+    pub async fn restore_container(&self, container_id: &str, name: &str) -> ContainerResult<()> {
+        validate_container_id_or_name(container_id)?;
+        validate_container_id_or_name(name)?;
+
+        self.run_checked(
+            "restore",
+            &restore_args(self.runtime_type, container_id, name),
+        )
+        .await?;
+
+        if self.runtime_type == RuntimeType::Docker {
+            let args = ["checkpoint", "rm", container_id, name].map(String::from);
+            if let Err(e) = self.run_checked("checkpoint rm", &args).await {
+                log::debug!("Failed to remove checkpoint {name} of {container_id}: {e}");
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Run the container binary, failing with its stderr when it exits
+    /// unsuccessfully.
+    async fn run_checked(&self, command: &str, args: &[String]) -> ContainerResult<()> {
+        let output = Command::new(&self.binary)
+            .args(args)
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .output()
+            .await
+            .map_err(|e| ContainerError::CommandFailed {
+                command: command.to_string(),
+                message: e.to_string(),
+            })?;
+
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            return Err(ContainerError::CommandFailed {
+                command: command.to_string(),
+                message: stderr.to_string(),
+            });
+        }
+
+        Ok(())
+    }
+
+    /// Change the CPU, memory and process limits of a container in place.
+    /// GPU passthrough only changes when the container is recreated.
+    pub async fn update_resources(
+        &self,
+        container_id: &str,
+        limits: &ResourceLimits,
+    ) -> ContainerResult<()> {
+        validate_container_id_or_name(container_id)?;
+        limits.validate()?;
+
+        let mut args = vec!["update".to_string()];
+        args.extend(resource_args(self.runtime_type, limits, false));
+        if args.len() == 1 {
+            return Ok(());
+        }
+        args.push(container_id.to_string());
+
+        let output = Command::new(&self.binary)
+            .args(&args)
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .output()
+            .await
+            .map_err(|e| ContainerError::CommandFailed {
+                command: "update".to_string(),
+                message: e.to_string(),
+            })?;
+
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            return Err(ContainerError::CommandFailed {
+                command: "update".to_string(),
+                message: stderr.to_string(),
+            });
+        }
+
+        Ok(())
+    }
+
     /// Remove a container.
     pub async fn remove_container(&self, container_id: &str, force: bool) -> ContainerResult<()> {
         validate_container_id_or_name(container_id)?;
@@ -570,8 +985,101 @@ impl ContainerRuntime {
         Ok(Some(status))
     }
 
+    /// Get how a stopped container exited via `inspect`.
+    ///
+    /// Returns `Ok(None)` when the container does not exist.
+    pub async fn container_exit_state(
+        &self,
+        id_or_name: &str,
+    ) -> ContainerResult<Option<ContainerExitState>> {
+        validate_container_id_or_name(id_or_name)?;
+
+        let output = Command::new(&self.binary)
+            .args([
+                "inspect",
+                "--format",
+                ContainerExitState::INSPECT_FORMAT,
+                id_or_name,
+            ])
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .output()
+            .await
+            .map_err(|e| ContainerError::CommandFailed {
+                command: "inspect".to_string(),
+                message: e.to_string(),
+            })?;
+
+        if !output.status.success() {
+            return Ok(None);
+        }
+
+        Ok(ContainerExitState::parse(&String::from_utf8_lossy(
+            &output.stdout,
+        )))
+    }
+
+    /// Get the address of a container on its first network via `inspect`.
+    ///
+    /// Returns `Ok(None)` when the container does not exist or has no address
+    /// (e.g. host networking).
+    pub async fn container_ip(&self, id_or_name: &str) -> ContainerResult<Option<IpAddr>> {
+        validate_container_id_or_name(id_or_name)?;
+
+        let output = Command::new(&self.binary)
+            .args([
+                "inspect",
+                "--format",
+                "{{range .NetworkSettings.Networks}}{{.IPAddress}} {{end}}",
+                id_or_name,
+            ])
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .output()
+            .await
+            .map_err(|e| ContainerError::CommandFailed {
+                command: "inspect".to_string(),
+                message: e.to_string(),
+            })?;
+
+        if !output.status.success() {
+            return Ok(None);
+        }
+
+        Ok(String::from_utf8_lossy(&output.stdout)
+            .split_whitespace()
+            .find_map(|addr| addr.parse().ok()))
+    }
+
+    /// Get the host pid of a running container's main process via `inspect`.
+    ///
+    /// Returns `Ok(None)` when the container does not exist or is not running.
+    pub async fn container_pid(&self, id_or_name: &str) -> ContainerResult<Option<u32>> {
+        validate_container_id_or_name(id_or_name)?;
+
+        let output = Command::new(&self.binary)
+            .args(["inspect", "--format", "{{.State.Pid}}", id_or_name])
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .output()
+            .await
+            .map_err(|e| ContainerError::CommandFailed {
+                command: "inspect".to_string(),
+                message: e.to_string(),
+            })?;
+
+        if !output.status.success() {
+            return Ok(None);
+        }
+
+        Ok(String::from_utf8_lossy(&output.stdout)
+            .trim()
+            .parse()
+            .ok()
+            .filter(|pid| *pid != 0))
+    }
+
     /// Get container logs.
-    #[allow(dead_code)]
     pub async fn get_logs(&self, container_id: &str, tail: Option<u32>) -> ContainerResult<String> {
         validate_container_id_or_name(container_id)?;
 
@@ -684,6 +1192,55 @@ impl ContainerRuntime {
         Ok(())
     }
 
+    /// Build an image from a Dockerfile. stdout and stderr of the build are
+    /// sent to `output` line by line; a receiver that went away only means
+    /// nobody is watching. The build is killed when the future is dropped.
+    pub async fn build_image(
+        &self,
+        spec: &ImageBuildSpec,
+        output: mpsc::Sender<String>,
+    ) -> ContainerResult<()> {
+        validate_image_name(&spec.tag)?;
+
+        let mut command = Command::new(&self.binary);
+        command
+            .arg("build")
+            .arg("--file")
+            .arg(&spec.dockerfile)
+            .arg("--tag")
+            .arg(&spec.tag);
+        for (key, value) in &spec.labels {
+            command.arg("--label").arg(format!("{key}={value}"));
+        }
+        let mut child = command
+            .arg(&spec.context)
+            // Plain BuildKit progress instead of a terminal view.
+            .env("BUILDKIT_PROGRESS", "plain")
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .kill_on_drop(true)
+            .spawn()
+            .map_err(|e| ContainerError::CommandFailed {
+                command: "build".to_string(),
+                message: e.to_string(),
+            })?;
+
+        let (stdout_last, stderr_last) = tokio::join!(
+            forward_lines(child.stdout.take(), output.clone()),
+            forward_lines(child.stderr.take(), output),
+        );
+
+        let status = child.wait().await?;
+        if !status.success() {
+            return Err(ContainerError::CommandFailed {
+                command: "build".to_string(),
+                message: stderr_last.or(stdout_last).unwrap_or_default(),
+            });
+        }
+        Ok(())
+    }
+
     /// Get the digest (sha256) for a local image.
     ///
     /// Returns `Ok(None)` if the image does not exist locally.
@@ -837,4 +1394,61 @@ mod tests {
         assert!(!RuntimeType::Docker.needs_selinux_labels());
         assert!(RuntimeType::Podman.needs_selinux_labels());
     }
+
+    #[test]
+    fn test_resource_args() {
+        let limits = ResourceLimits {
+            cpu_shares: Some(512),
+            memory_mb: Some(2048),
+            pids_limit: Some(256),
+            gpus: Some("0,1".to_string()),
+        };
+        assert_eq!(
+            resource_args(RuntimeType::Docker, &limits, true),
+            [
+                "--cpu-shares",
+                "512",
+                "--memory",
+                "2048m",
+                "--memory-swap",
+                "2048m",
+                "--pids-limit",
+                "256",
+                "--gpus",
+                "\"device=0,1\"",
+            ]
+        );
+        let podman = resource_args(RuntimeType::Podman, &limits, true);
+        assert_eq!(
+            podman[podman.len() - 4..],
+            [
+                "--device",
+                "nvidia.com/gpu=0",
+                "--device",
+                "nvidia.com/gpu=1"
+            ]
+        );
+        assert_eq!(resource_args(RuntimeType::Docker, &limits, false).len(), 8);
+        assert!(resource_args(RuntimeType::Docker, &ResourceLimits::default(), true).is_empty());
+    }
+
+    #[test]
+    fn test_checkpoint_args() {
+        assert_eq!(
+            checkpoint_args(RuntimeType::Docker, "oqto-abc", "hibernate-1"),
+            ["checkpoint", "create", "oqto-abc", "hibernate-1"]
+        );
+        assert_eq!(
+            restore_args(RuntimeType::Docker, "oqto-abc", "hibernate-1"),
+            ["start", "--checkpoint", "hibernate-1", "oqto-abc"]
+        );
+        assert_eq!(
+            checkpoint_args(RuntimeType::Podman, "oqto-abc", "hibernate-1"),
+            ["container", "checkpoint", "--tcp-established", "oqto-abc"]
+        );
+        assert_eq!(
+            restore_args(RuntimeType::Podman, "oqto-abc", "hibernate-1"),
+            ["container", "restore", "--tcp-established", "oqto-abc"]
+        );
+    }
 }
@@ -0,0 +1,1115 @@
+//! Container runtime talking to the Docker Engine API on a unix socket.
+//!
+//! Docker and Podman both serve the Docker-compatible API, Podman also
+//! rootless (`systemctl --user enable --now podman.socket`). Unlike the CLI
+//! runtime this needs no binary on `PATH`, failures carry the engine's own
+//! error message, and stats are read from structured JSON instead of
+//! formatted `stats` columns. Checkpoints are not supported.
+
+use std::collections::HashMap;
+use std::io::{Read, Seek};
+use std::net::IpAddr;
+use std::path::{Path, PathBuf};
+
+use async_trait::async_trait;
+use http_body_util::{BodyExt, Full};
+use hyper::body::{Bytes, Incoming};
+use hyper::{Method, Request, Response, StatusCode};
+use hyper_util::client::legacy::Client;
+use hyper_util::rt::TokioExecutor;
+use hyperlocal::{UnixConnector, Uri as UnixUri};
+use serde::Deserialize;
+use serde_json::{Map, Value, json};
+use tokio::sync::mpsc;
+
+use super::container::{ContainerPort, ContainerState};
+use super::{
+    Container, ContainerConfig, ContainerError, ContainerExitState, ContainerResult,
+    ContainerRuntimeApi, ContainerStats, ImageBuildSpec, ResourceLimits, RuntimeType,
+    validate_container_id_or_name, validate_image_name,
+};
+use crate::observability::metrics::metrics;
+
+/// Largest build context (as a tar archive) sent to the engine.
+const MAX_BUILD_CONTEXT_BYTES: u64 = 1024 * 1024 * 1024;
+
+/// Socket of the local engine: `CONTAINER_HOST` or `DOCKER_HOST` when they
+/// name a unix socket, else the first existing rootless Podman, rootful
+/// Podman or Docker socket.
+pub fn default_socket_path() -> Option<PathBuf> {
+    for var in ["CONTAINER_HOST", "DOCKER_HOST"] {
+        if let Ok(host) = std::env::var(var)
+            && let Some(path) = host.strip_prefix("unix://")
+        {
+            return Some(PathBuf::from(path));
+        }
+    }
+    let mut candidates = Vec::new();
+    if let Some(runtime_dir) = std::env::var_os("XDG_RUNTIME_DIR") {
+        candidates.push(PathBuf::from(runtime_dir).join("podman/podman.sock"));
+    }
+    candidates.push(PathBuf::from("/run/podman/podman.sock"));
+    candidates.push(PathBuf::from("/var/run/docker.sock"));
+    candidates.into_iter().find(|path| path.exists())
+}
+
+/// Container runtime client using the engine's REST API.
+#[derive(Clone)]
+pub struct SocketRuntime {
+    socket: PathBuf,
+    /// Decides Podman-only options (SELinux labels, pasta networking, CDI
+    /// GPUs), as with the CLI runtime.
+    runtime_type: RuntimeType,
+    client: Client<UnixConnector, Full<Bytes>>,
+}
+
+impl SocketRuntime {
+    /// Client for the engine at `socket`. Without a `runtime_type`, a
+    /// socket path containing "podman" is taken to be Podman.
+    pub fn new(socket: impl Into<PathBuf>, runtime_type: Option<RuntimeType>) -> Self {
+        let socket = socket.into();
+        let runtime_type = runtime_type.unwrap_or_else(|| {
+            if socket.to_string_lossy().contains("podman") {
+                RuntimeType::Podman
+            } else {
+                RuntimeType::Docker
+            }
+        });
+        Self {
+            socket,
+            runtime_type,
+            client: Client::builder(TokioExecutor::new()).build(UnixConnector),
+        }
+    }
+
+    /// Get the runtime type.
+    pub fn runtime_type(&self) -> RuntimeType {
+        self.runtime_type
+    }
+
+    /// Path of the API socket.
+    pub fn socket(&self) -> &Path {
+        &self.socket
+    }
+
+    /// Check if the engine answers; returns its version information.
+    pub async fn health_check(&self) -> ContainerResult<String> {
+        let (status, body) = self
+            .request("version", Method::GET, "/version", None)
+            .await?;
+        if !status.is_success() {
+            return Err(api_error("version", status, &body));
+        }
+        Ok(String::from_utf8_lossy(&body).to_string())
+    }
+
+    /// Pull an image.
+    pub async fn pull_image(&self, image: &str) -> ContainerResult<()> {
+        validate_image_name(image)?;
+
+        let path = format!("/images/create?fromImage={}", urlencoding::encode(image));
+        let (status, body) = self.request("pull", Method::POST, &path, None).await?;
+        if !status.is_success() {
+            return Err(api_error("pull", status, &body));
+        }
+        // Failures after the pull started come as an error in the progress
+        // stream of a successful response.
+        for line in body.split(|b| *b == b'\n') {
+            if let (_, Some(message)) = parse_progress(line) {
+                return Err(ContainerError::CommandFailed {
+                    command: "pull".to_string(),
+                    message,
+                });
+            }
+        }
+        Ok(())
+    }
+
+    async fn send(
+        &self,
+        command: &str,
+        method: Method,
+        path: &str,
+        content_type: &str,
+        body: Bytes,
+    ) -> ContainerResult<Response<Incoming>> {
+        let uri: hyper::Uri = UnixUri::new(&self.socket, path).into();
+        let mut builder = Request::builder().method(method).uri(uri);
+        if !content_type.is_empty() {
+            builder = builder.header("content-type", content_type);
+        }
+        let request = builder
+            .body(Full::new(body))
+            .map_err(|e| failed(command, e))?;
+        self.client.request(request).await.map_err(|e| {
+            failed(
+                command,
+                format!("cannot reach {}: {e}", self.socket.display()),
+            )
+        })
+    }
+
+    /// Send a request with an optional JSON body; returns status and body.
+    async fn request(
+        &self,
+        command: &str,
+        method: Method,
+        path: &str,
+        body: Option<Value>,
+    ) -> ContainerResult<(StatusCode, Bytes)> {
+        let (content_type, body) = match body {
+            Some(body) => ("application/json", Bytes::from(body.to_string())),
+            None => ("", Bytes::new()),
+        };
+        let response = self.send(command, method, path, content_type, body).await?;
+        let status = response.status();
+        let body = response
+            .into_body()
+            .collect()
+            .await
+            .map_err(|e| failed(command, e))?
+            .to_bytes();
+        Ok((status, body))
+    }
+
+    /// Call `/containers/{id}{suffix}`. A missing container is
+    /// `ContainerNotFound`; "not modified" (already started or stopped)
+    /// counts as success.
+    async fn container_call(
+        &self,
+        command: &str,
+        method: Method,
+        id: &str,
+        suffix: &str,
+        body: Option<Value>,
+    ) -> ContainerResult<Bytes> {
+        validate_container_id_or_name(id)?;
+
+        let path = format!("/containers/{id}{suffix}");
+        let (status, response) = self.request(command, method, &path, body).await?;
+        match status {
+            s if s.is_success() || s == StatusCode::NOT_MODIFIED => Ok(response),
+            StatusCode::NOT_FOUND => Err(ContainerError::ContainerNotFound(id.to_string())),
+            _ => Err(api_error(command, status, &response)),
+        }
+    }
+
+    /// `inspect` output of a container; None when it does not exist.
+    async fn inspect(&self, id_or_name: &str) -> ContainerResult<Option<Value>> {
+        match self
+            .container_call("inspect", Method::GET, id_or_name, "/json", None)
+            .await
+        {
+            Ok(body) => serde_json::from_slice(&body)
+                .map(Some)
+                .map_err(|e| ContainerError::ParseError(e.to_string())),
+            Err(ContainerError::ContainerNotFound(_)) => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Create an exec instance running `command` and start it. Returns the
+    /// exec ID and, unless detached, the multiplexed output.
+    async fn exec(
+        &self,
+        container_id: &str,
+        command: &[&str],
+        detach: bool,
+    ) -> ContainerResult<(String, Bytes)> {
+        let created = self
+            .container_call(
+                "exec",
+                Method::POST,
+                container_id,
+                "/exec",
+                Some(json!({
+                    "Cmd": command,
+                    "AttachStdout": !detach,
+                    "AttachStderr": !detach,
+                })),
+            )
+            .await?;
+        let exec_id = serde_json::from_slice::<Value>(&created)
+            .ok()
+            .and_then(|v| v.get("Id")?.as_str().map(str::to_string))
+            .ok_or_else(|| ContainerError::ParseError("exec response without Id".to_string()))?;
+
+        let path = format!("/exec/{exec_id}/start");
+        let body = json!({ "Detach": detach, "Tty": false });
+        let (status, output) = self
+            .request("exec", Method::POST, &path, Some(body))
+            .await?;
+        if !status.is_success() {
+            return Err(api_error("exec", status, &output));
+        }
+        Ok((exec_id, output))
+    }
+}
+
+#[async_trait]
+impl ContainerRuntimeApi for SocketRuntime {
+    async fn create_container(&self, config: &ContainerConfig) -> ContainerResult<String> {
+        let id = self.create_stopped_container(config).await?;
+        self.container_call("run", Method::POST, &id, "/start", None)
+            .await?;
+        metrics().container_started();
+        Ok(id)
+    }
+
+    async fn create_stopped_container(&self, config: &ContainerConfig) -> ContainerResult<String> {
+        config.validate()?;
+
+        let mut path = "/containers/create".to_string();
+        if let Some(ref name) = config.name {
+            path.push_str(&format!("?name={name}"));
+        }
+        let body = create_body(self.runtime_type, config);
+        let (mut status, mut response) = self
+            .request("run", Method::POST, &path, Some(body.clone()))
+            .await?;
+        // Pull a missing image like `run` does.
+        if status == StatusCode::NOT_FOUND {
+            self.pull_image(&config.image).await?;
+            (status, response) = self.request("run", Method::POST, &path, Some(body)).await?;
+        }
+        if !status.is_success() {
+            return Err(api_error("run", status, &response));
+        }
+        let id = serde_json::from_slice::<Value>(&response)
+            .ok()
+            .and_then(|v| v.get("Id")?.as_str().map(str::to_string))
+            .ok_or_else(|| ContainerError::ParseError("create response without Id".to_string()))?;
+        Ok(id)
+    }
+
+    /// Podman's libpod `init`; the Docker API has no equivalent.
+    async fn hold_container(&self, container_id: &str) -> ContainerResult<()> {
+        validate_container_id_or_name(container_id)?;
+
+        if self.runtime_type != RuntimeType::Podman {
+            return Err(failed(
+                "init",
+                "only Podman can hold a container before its start",
+            ));
+        }
+        let path = format!("/v4.0.0/libpod/containers/{container_id}/init");
+        let (status, response) = self.request("init", Method::POST, &path, None).await?;
+        match status {
+            s if s.is_success() || s == StatusCode::NOT_MODIFIED => Ok(()),
+            StatusCode::NOT_FOUND => {
+                Err(ContainerError::ContainerNotFound(container_id.to_string()))
+            }
+            _ => Err(api_error("init", status, &response)),
+        }
+    }
+
+    async fn stop_container(
+        &self,
+        container_id: &str,
+        timeout_seconds: Option<u32>,
+    ) -> ContainerResult<()> {
+        let suffix = match timeout_seconds {
+            Some(t) => format!("/stop?t={t}"),
+            None => "/stop".to_string(),
+        };
+        self.container_call("stop", Method::POST, container_id, &suffix, None)
+            .await?;
+        metrics().container_stopped();
+        Ok(())
+    }
+
+    async fn start_container(&self, container_id: &str) -> ContainerResult<()> {
+        self.container_call("start", Method::POST, container_id, "/start", None)
+            .await?;
+        metrics().container_started();
+        Ok(())
+    }
+
+    async fn remove_container(&self, container_id: &str, force: bool) -> ContainerResult<()> {
+        let suffix = format!("?force={force}");
+        self.container_call("rm", Method::DELETE, container_id, &suffix, None)
+            .await?;
+        Ok(())
+    }
+
+    async fn list_containers(&self, all: bool) -> ContainerResult<Vec<Container>> {
+        let path = format!("/containers/json?all={all}");
+        let (status, body) = self.request("ps", Method::GET, &path, None).await?;
+        if !status.is_success() {
+            return Err(api_error("ps", status, &body));
+        }
+        let containers: Vec<ApiContainer> =
+            serde_json::from_slice(&body).map_err(|e| ContainerError::ParseError(e.to_string()))?;
+        Ok(containers.into_iter().map(Container::from).collect())
+    }
+
+    async fn container_state_status(&self, id_or_name: &str) -> ContainerResult<Option<String>> {
+        Ok(self.inspect(id_or_name).await?.and_then(|v| {
+            v.pointer("/State/Status")
+                .and_then(Value::as_str)
+                .filter(|s| !s.is_empty())
+                .map(str::to_string)
+        }))
+    }
+
+    async fn container_exit_state(
+        &self,
+        id_or_name: &str,
+    ) -> ContainerResult<Option<ContainerExitState>> {
+        Ok(self.inspect(id_or_name).await?.map(|v| ContainerExitState {
+            exit_code: v
+                .pointer("/State/ExitCode")
+                .and_then(Value::as_i64)
+                .unwrap_or_default() as i32,
+            oom_killed: v
+                .pointer("/State/OOMKilled")
+                .and_then(Value::as_bool)
+                .unwrap_or_default(),
+            memory_limit: v
+                .pointer("/HostConfig/Memory")
+                .and_then(Value::as_u64)
+                .filter(|m| *m > 0),
+            error: v
+                .pointer("/State/Error")
+                .and_then(Value::as_str)
+                .unwrap_or_default()
+                .to_string(),
+        }))
+    }
+
+    async fn container_ip(&self, id_or_name: &str) -> ContainerResult<Option<IpAddr>> {
+        Ok(self.inspect(id_or_name).await?.and_then(|v| {
+            v.pointer("/NetworkSettings/Networks")?
+                .as_object()?
+                .values()
+                .find_map(|network| network.get("IPAddress")?.as_str()?.parse().ok())
+        }))
+    }
+
+    async fn container_pid(&self, id_or_name: &str) -> ContainerResult<Option<u32>> {
+        Ok(self.inspect(id_or_name).await?.and_then(|v| {
+            v.pointer("/State/Pid")?
+                .as_u64()
+                .and_then(|pid| u32::try_from(pid).ok())
+                .filter(|pid| *pid != 0)
+        }))
+    }
+
+    async fn tail_logs(&self, container_id: &str, lines: u32) -> ContainerResult<String> {
+        let suffix = format!("/logs?stdout=true&stderr=true&tail={lines}");
+        let raw = self
+            .container_call("logs", Method::GET, container_id, &suffix, None)
+            .await?;
+        let (stdout, stderr) = demux(&raw);
+        Ok(format!(
+            "{}{}",
+            String::from_utf8_lossy(&stdout),
+            String::from_utf8_lossy(&stderr)
+        ))
+    }
+
+    async fn get_image_digest(&self, image: &str) -> ContainerResult<Option<String>> {
+        validate_image_name(image)?;
+
+        let path = format!("/images/{image}/json");
+        let (status, body) = self
+            .request("image inspect", Method::GET, &path, None)
+            .await?;
+        if status == StatusCode::NOT_FOUND {
+            return Ok(None);
+        }
+        if !status.is_success() {
+            return Err(api_error("image inspect", status, &body));
+        }
+        let image: Value =
+            serde_json::from_slice(&body).map_err(|e| ContainerError::ParseError(e.to_string()))?;
+        // The registry digest, else the image ID for local builds.
+        let digest = image
+            .get("RepoDigests")
+            .and_then(Value::as_array)
+            .and_then(|digests| digests.first()?.as_str()?.split_once('@'))
+            .map(|(_, digest)| digest.to_string())
+            .or_else(|| image.get("Id")?.as_str().map(str::to_string))
+            .filter(|digest| !digest.is_empty());
+        Ok(digest)
+    }
+
+    async fn build_image(
+        &self,
+        spec: &ImageBuildSpec,
+        output: mpsc::Sender<String>,
+    ) -> ContainerResult<()> {
+        validate_image_name(&spec.tag)?;
+
+        let context = spec.context.canonicalize()?;
+        let dockerfile = spec
+            .dockerfile
+            .strip_prefix(&context)
+            .map_err(|_| {
+                ContainerError::InvalidInput("Dockerfile is outside the build context".to_string())
+            })?
+            .to_string_lossy()
+            .to_string();
+        let archive = tokio::task::spawn_blocking(move || context_archive(&context))
+            .await
+            .map_err(|e| failed("build", e))??;
+        let labels: HashMap<&str, &str> = spec
+            .labels
+            .iter()
+            .map(|(k, v)| (k.as_str(), v.as_str()))
+            .collect();
+        let path = format!(
+            "/build?t={}&dockerfile={}&labels={}&rm=true",
+            urlencoding::encode(&spec.tag),
+            urlencoding::encode(&dockerfile),
+            urlencoding::encode(&json!(labels).to_string()),
+        );
+
+        // Dropping the request (timeout) closes the connection, which makes
+        // the engine cancel the build.
+        let response = self
+            .send(
+                "build",
+                Method::POST,
+                &path,
+                "application/x-tar",
+                archive.into(),
+            )
+            .await?;
+        let status = response.status();
+        if !status.is_success() {
+            let body = response
+                .into_body()
+                .collect()
+                .await
+                .map(|b| b.to_bytes())
+                .unwrap_or_default();
+            return Err(api_error("build", status, &body));
+        }
+
+        let mut body = response.into_body();
+        let mut pending = Vec::new();
+        let mut error = None;
+        while let Some(frame) = body.frame().await {
+            let frame = frame.map_err(|e| failed("build", e))?;
+            let Ok(data) = frame.into_data() else {
+                continue;
+            };
+            pending.extend_from_slice(&data);
+            while let Some(end) = pending.iter().position(|b| *b == b'\n') {
+                let line: Vec<u8> = pending.drain(..=end).collect();
+                forward_progress(&line, &output, &mut error).await;
+            }
+        }
+        forward_progress(&pending, &output, &mut error).await;
+
+        match error {
+            Some(message) => Err(ContainerError::CommandFailed {
+                command: "build".to_string(),
+                message,
+            }),
+            None => Ok(()),
+        }
+    }
+
+    async fn update_resources(
+        &self,
+        container_id: &str,
+        limits: &ResourceLimits,
+    ) -> ContainerResult<()> {
+        limits.validate()?;
+
+        let fields = limit_fields(limits);
+        if fields.is_empty() {
+            return Ok(());
+        }
+        self.container_call(
+            "update",
+            Method::POST,
+            container_id,
+            "/update",
+            Some(Value::Object(fields)),
+        )
+        .await?;
+        Ok(())
+    }
+
+    async fn get_stats(&self, container_id: &str) -> ContainerResult<ContainerStats> {
+        let body = self
+            .container_call(
+                "stats",
+                Method::GET,
+                container_id,
+                "/stats?stream=false",
+                None,
+            )
+            .await?;
+        let stats: ApiStats =
+            serde_json::from_slice(&body).map_err(|e| ContainerError::ParseError(e.to_string()))?;
+        Ok(stats.into_container_stats(container_id))
+    }
+
+    async fn exec_detached(&self, container_id: &str, command: &[&str]) -> ContainerResult<()> {
+        self.exec(container_id, command, true).await?;
+        Ok(())
+    }
+
+    async fn exec_output(&self, container_id: &str, command: &[&str]) -> ContainerResult<String> {
+        let (exec_id, raw) = self.exec(container_id, command, false).await?;
+        let (stdout, stderr) = demux(&raw);
+
+        let path = format!("/exec/{exec_id}/json");
+        let (status, body) = self.request("exec", Method::GET, &path, None).await?;
+        if !status.is_success() {
+            return Err(api_error("exec", status, &body));
+        }
+        let exit_code = serde_json::from_slice::<Value>(&body)
+            .ok()
+            .and_then(|v| v.get("ExitCode")?.as_i64())
+            .unwrap_or_default();
+        if exit_code != 0 {
+            return Err(ContainerError::CommandFailed {
+                command: "exec".to_string(),
+                message: String::from_utf8_lossy(&stderr).to_string(),
+            });
+        }
+        Ok(String::from_utf8_lossy(&stdout).to_string())
+    }
+}
+
+fn failed(command: &str, message: impl ToString) -> ContainerError {
+    ContainerError::CommandFailed {
+        command: command.to_string(),
+        message: message.to_string(),
+    }
+}
+
+/// The error of a failed call, with the engine's `message`.
+fn api_error(command: &str, status: StatusCode, body: &[u8]) -> ContainerError {
+    let message = serde_json::from_slice::<Value>(body)
+        .ok()
+        .and_then(|v| v.get("message")?.as_str().map(str::to_string))
+        .unwrap_or_else(|| String::from_utf8_lossy(body).trim().to_string());
+    failed(command, format!("{message} (HTTP {})", status.as_u16()))
+}
+
+/// Send the output lines of a progress message to `output`, keeping the
+/// error it reports.
+async fn forward_progress(line: &[u8], output: &mpsc::Sender<String>, error: &mut Option<String>) {
+    let (lines, message) = parse_progress(line);
+    for line in lines {
+        let _ = output.send(line).await;
+    }
+    if message.is_some() {
+        *error = message;
+    }
+}
+
+/// `/containers/create` body for `config`, with the same options the CLI
+/// runtime passes to `run`.
+fn create_body(runtime_type: RuntimeType, config: &ContainerConfig) -> Value {
+    let host_network = config.network_mode.as_deref() == Some("host");
+    let bind = |host: &str, container: &str, read_only: bool| {
+        let mut options = Vec::new();
+        if read_only {
+            options.push("ro");
+        }
+        if runtime_type.needs_selinux_labels() {
+            options.push("Z");
+        }
+        if options.is_empty() {
+            format!("{host}:{container}")
+        } else {
+            format!("{host}:{container}:{}", options.join(","))
+        }
+    };
+    let binds: Vec<String> = config
+        .volumes
+        .iter()
+        .map(|(host, container)| bind(host, container, false))
+        .chain(
+            config
+                .read_only_volumes
+                .iter()
+                .map(|(host, container)| bind(host, container, true)),
+        )
+        .collect();
+
+    let mut exposed_ports = Map::new();
+    let mut port_bindings = Map::new();
+    if !host_network {
+        for port in &config.ports {
+            let key = format!("{}/{}", port.container_port, port.protocol);
+            exposed_ports.insert(key.clone(), json!({}));
+            port_bindings.insert(key, json!([{ "HostPort": port.host_port.to_string() }]));
+        }
+    }
+
+    let mut host_config = limit_fields(&config.resources);
+    host_config.insert("Binds".to_string(), json!(binds));
+    host_config.insert("PortBindings".to_string(), Value::Object(port_bindings));
+    let network_mode = match (&config.network_mode, runtime_type) {
+        (Some(mode), _) => Some(mode.as_str()),
+        // Same MTU fix as the CLI runtime's default pasta network.
+        (None, RuntimeType::Podman) => Some("pasta:-m,1500"),
+        (None, RuntimeType::Docker) => None,
+    };
+    if let Some(mode) = network_mode {
+        host_config.insert("NetworkMode".to_string(), json!(mode));
+    }
+    let devices = config.resources.gpu_devices();
+    if !devices.is_empty() {
+        match runtime_type {
+            RuntimeType::Docker => {
+                let mut request = json!({ "Driver": "nvidia", "Capabilities": [["gpu"]] });
+                if devices == ["all"] {
+                    request["Count"] = json!(-1);
+                } else {
+                    request["DeviceIDs"] = json!(devices);
+                }
+                host_config.insert("DeviceRequests".to_string(), json!([request]));
+            }
+            RuntimeType::Podman => {
+                let devices: Vec<Value> = devices
+                    .iter()
+                    .map(|device| {
+                        json!({
+                            "PathOnHost": format!("nvidia.com/gpu={device}"),
+                            "PathInContainer": "",
+                            "CgroupPermissions": "rwm",
+                        })
+                    })
+                    .collect();
+                host_config.insert("Devices".to_string(), json!(devices));
+            }
+        }
+    }
+
+    let env: Vec<String> = config
+        .env
+        .iter()
+        .map(|(key, value)| format!("{key}={value}"))
+        .collect();
+    let mut body = json!({
+        "Image": config.image,
+        "Env": env,
+        "Labels": config.labels,
+        "ExposedPorts": exposed_ports,
+        "HostConfig": host_config,
+    });
+    if let Some(ref hostname) = config.hostname
+        && !host_network
+    {
+        body["Hostname"] = json!(hostname);
+    }
+    if let Some(ref workdir) = config.workdir {
+        body["WorkingDir"] = json!(workdir);
+    }
+    if !config.command.is_empty() {
+        body["Cmd"] = json!(config.command);
+    }
+    body
+}
+
+/// `HostConfig` fields applying `limits`; GPUs are handled at creation.
+fn limit_fields(limits: &ResourceLimits) -> Map<String, Value> {
+    let mut fields = Map::new();
+    if let Some(shares) = limits.cpu_shares {
+        fields.insert("CpuShares".to_string(), json!(shares));
+    }
+    if let Some(memory) = limits.memory_mb {
+        // Equal swap limit: the memory limit covers swap too.
+        let bytes = u64::from(memory) * 1024 * 1024;
+        fields.insert("Memory".to_string(), json!(bytes));
+        fields.insert("MemorySwap".to_string(), json!(bytes));
+    }
+    if let Some(pids) = limits.pids_limit {
+        fields.insert("PidsLimit".to_string(), json!(pids));
+    }
+    fields
+}
+
+/// The build context as a tar archive. Symlinks are archived as links.
+fn context_archive(context: &Path) -> ContainerResult<Vec<u8>> {
+    let mut builder = tar::Builder::new(tempfile::tempfile()?);
+    builder.follow_symlinks(false);
+    builder.append_dir_all(".", context)?;
+    let mut file = builder.into_inner()?;
+    if file.metadata()?.len() > MAX_BUILD_CONTEXT_BYTES {
+        return Err(ContainerError::InvalidInput(format!(
+            "build context is larger than {} MiB",
+            MAX_BUILD_CONTEXT_BYTES / 1024 / 1024
+        )));
+    }
+    file.rewind()?;
+    let mut archive = Vec::new();
+    file.read_to_end(&mut archive)?;
+    Ok(archive)
+}
+
+/// Output lines and error of one JSON message of a build or pull
+/// progress stream.
+fn parse_progress(line: &[u8]) -> (Vec<String>, Option<String>) {
+    if line.iter().all(u8::is_ascii_whitespace) {
+        return (Vec::new(), None);
+    }
+    let Ok(message) = serde_json::from_slice::<Value>(line) else {
+        let text = String::from_utf8_lossy(line).trim_end().to_string();
+        return (vec![text], None);
+    };
+    let mut lines: Vec<String> = message
+        .get("stream")
+        .and_then(Value::as_str)
+        .map(|stream| stream.lines().map(str::to_string).collect())
+        .unwrap_or_default();
+    let error = message
+        .get("error")
+        .or_else(|| message.pointer("/errorDetail/message"))
+        .and_then(Value::as_str)
+        .map(str::to_string);
+    if let Some(ref error) = error {
+        lines.push(error.clone());
+    }
+    (lines, error)
+}
+
+/// Split attached output into stdout and stderr. Without a TTY the engine
+/// prefixes each chunk with an 8-byte header: stream (1 stdout, 2 stderr),
+/// three zero bytes and the big-endian length. Anything else is raw stdout.
+fn demux(raw: &[u8]) -> (Vec<u8>, Vec<u8>) {
+    let mut stdout = Vec::new();
+    let mut stderr = Vec::new();
+    let mut rest = raw;
+    while rest.len() >= 8 && rest[0] <= 2 && rest[1..4] == [0, 0, 0] {
+        let len = u32::from_be_bytes([rest[4], rest[5], rest[6], rest[7]]) as usize;
+        let end = rest.len().min(8 + len);
+        if rest[0] == 2 {
+            stderr.extend_from_slice(&rest[8..end]);
+        } else {
+            stdout.extend_from_slice(&rest[8..end]);
+        }
+        rest = &rest[end..];
+    }
+    stdout.extend_from_slice(rest);
+    (stdout, stderr)
+}
+
+/// Entry of `/containers/json`.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct ApiContainer {
+    id: String,
+    #[serde(default)]
+    names: Vec<String>,
+    #[serde(default)]
+    image: String,
+    #[serde(default)]
+    state: ContainerState,
+    #[serde(default)]
+    status: String,
+    #[serde(default)]
+    created: i64,
+    #[serde(default)]
+    ports: Vec<ApiPort>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct ApiPort {
+    #[serde(default, rename = "IP")]
+    ip: String,
+    #[serde(default)]
+    private_port: u16,
+    #[serde(default)]
+    public_port: Option<u16>,
+    #[serde(default, rename = "Type")]
+    protocol: String,
+}
+
+impl From<ApiContainer> for Container {
+    fn from(container: ApiContainer) -> Self {
+        Container {
+            id: container.id,
+            names: container
+                .names
+                .into_iter()
+                .map(|name| name.trim_start_matches('/').to_string())
+                .collect(),
+            image: container.image,
+            state: container.state,
+            status: container.status,
+            created: container.created.to_string(),
+            ports: container
+                .ports
+                .into_iter()
+                .filter_map(|port| {
+                    Some(ContainerPort {
+                        host_ip: port.ip,
+                        host_port: port.public_port?,
+                        container_port: port.private_port,
+                        protocol: port.protocol,
+                    })
+                })
+                .collect(),
+        }
+    }
+}
+
+/// `/containers/{id}/stats?stream=false` response. Engines leave out or
+/// null fields they cannot read (e.g. block I/O on some cgroup setups).
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct ApiStats {
+    name: Option<String>,
+    cpu_stats: CpuStats,
+    precpu_stats: CpuStats,
+    memory_stats: MemoryStats,
+    networks: Option<HashMap<String, NetworkStats>>,
+    blkio_stats: BlkioStats,
+    pids_stats: PidsStats,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct CpuStats {
+    cpu_usage: CpuUsage,
+    system_cpu_usage: Option<u64>,
+    online_cpus: Option<u64>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct CpuUsage {
+    total_usage: u64,
+    percpu_usage: Option<Vec<u64>>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct MemoryStats {
+    usage: Option<u64>,
+    limit: Option<u64>,
+    stats: Option<HashMap<String, u64>>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct NetworkStats {
+    rx_bytes: u64,
+    tx_bytes: u64,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct BlkioStats {
+    io_service_bytes_recursive: Option<Vec<BlkioEntry>>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct BlkioEntry {
+    op: String,
+    value: u64,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct PidsStats {
+    current: Option<u64>,
+}
+
+impl ApiStats {
+    /// The stats in the form `docker stats` prints them, which is what
+    /// the rest of the backend reads.
+    fn into_container_stats(self, container_id: &str) -> ContainerStats {
+        let cpu_delta = self
+            .cpu_stats
+            .cpu_usage
+            .total_usage
+            .saturating_sub(self.precpu_stats.cpu_usage.total_usage);
+        let system_delta = self
+            .cpu_stats
+            .system_cpu_usage
+            .unwrap_or_default()
+            .saturating_sub(self.precpu_stats.system_cpu_usage.unwrap_or_default());
+        let cpus = self
+            .cpu_stats
+            .online_cpus
+            .or_else(|| {
+                let percpu = self.cpu_stats.cpu_usage.percpu_usage.as_ref()?;
+                Some(percpu.len() as u64)
+            })
+            .filter(|n| *n > 0)
+            .unwrap_or(1);
+        let cpu_percent = if system_delta > 0 {
+            cpu_delta as f64 / system_delta as f64 * cpus as f64 * 100.0
+        } else {
+            0.0
+        };
+
+        // Like `docker stats`, page cache that can be reclaimed is not
+        // counted as used.
+        let memory = &self.memory_stats;
+        let cache = memory
+            .stats
+            .as_ref()
+            .and_then(|stats| {
+                ["inactive_file", "total_inactive_file", "cache"]
+                    .iter()
+                    .find_map(|key| stats.get(*key).copied())
+            })
+            .unwrap_or_default();
+        let used = memory.usage.unwrap_or_default().saturating_sub(cache);
+        let limit = memory.limit.unwrap_or_default();
+        let mem_percent = if limit > 0 {
+            used as f64 / limit as f64 * 100.0
+        } else {
+            0.0
+        };
+
+        let (rx, tx) = self
+            .networks
+            .iter()
+            .flat_map(HashMap::values)
+            .fold((0, 0), |(rx, tx), n| (rx + n.rx_bytes, tx + n.tx_bytes));
+        let (read, write) = self
+            .blkio_stats
+            .io_service_bytes_recursive
+            .iter()
+            .flatten()
+            .fold((0, 0), |(read, write), entry| {
+                match entry.op.to_ascii_lowercase().as_str() {
+                    "read" => (read + entry.value, write),
+                    "write" => (read, write + entry.value),
+                    _ => (read, write),
+                }
+            });
+
+        ContainerStats {
+            container_id: container_id.to_string(),
+            name: self
+                .name
+                .unwrap_or_default()
+                .trim_start_matches('/')
+                .to_string(),
+            cpu_percent: format!("{cpu_percent:.2}%"),
+            mem_usage: format!("{} / {}", format_size(used), format_size(limit)),
+            mem_percent: format!("{mem_percent:.2}%"),
+            net_io: format!("{} / {}", format_size(rx), format_size(tx)),
+            block_io: format!("{} / {}", format_size(read), format_size(write)),
+            pids: self.pids_stats.current.unwrap_or_default().to_string(),
+        }
+    }
+}
+
+/// `bytes` in binary units, as `docker stats` prints sizes.
+pub(super) fn format_size(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["KiB", "MiB", "GiB", "TiB"];
+    if bytes < 1024 {
+        return format!("{bytes}B");
+    }
+    let mut value = bytes as f64 / 1024.0;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    format!("{value:.2}{}", UNITS[unit])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::session::resources::{parse_memory_usage, parse_percent};
+
+    #[test]
+    fn stats_and_logs_are_read_from_api_responses() {
+        let stats: ApiStats = serde_json::from_value(json!({
+            "name": "/oqto-ses_1",
+            "cpu_stats": {
+                "cpu_usage": { "total_usage": 3_000_000 },
+                "system_cpu_usage": 20_000_000,
+                "online_cpus": 4
+            },
+            "precpu_stats": {
+                "cpu_usage": { "total_usage": 2_000_000 },
+                "system_cpu_usage": 12_000_000
+            },
+            "memory_stats": {
+                "usage": 600 * 1024 * 1024,
+                "limit": 2048_u64 * 1024 * 1024,
+                "stats": { "inactive_file": 88 * 1024 * 1024 }
+            },
+            "networks": { "eth0": { "rx_bytes": 2048, "tx_bytes": 512 } },
+            "blkio_stats": { "io_service_bytes_recursive": null },
+            "pids_stats": { "current": 17 }
+        }))
+        .unwrap();
+        let stats = stats.into_container_stats("abc123");
+        assert_eq!(stats.name, "oqto-ses_1");
+        assert_eq!(parse_percent(&stats.cpu_percent), Some(50.0));
+        assert_eq!(
+            parse_memory_usage(&stats.mem_usage),
+            (Some(512 * 1024 * 1024), Some(2048 * 1024 * 1024))
+        );
+        assert_eq!(stats.net_io, "2.00KiB / 512B");
+        assert_eq!(stats.pids, "17");
+
+        let mut raw = vec![1, 0, 0, 0, 0, 0, 0, 3];
+        raw.extend_from_slice(b"out");
+        raw.extend_from_slice(&[2, 0, 0, 0, 0, 0, 0, 4]);
+        raw.extend_from_slice(b"err\n");
+        assert_eq!(demux(&raw), (b"out".to_vec(), b"err\n".to_vec()));
+        assert_eq!(
+            demux(b"plain tty output"),
+            (b"plain tty output".to_vec(), vec![])
+        );
+    }
+
+    #[test]
+    fn create_body_matches_the_cli_options() {
+        let config = ContainerConfig::new("oqto:latest")
+            .name("oqto-ses_1")
+            .hostname("ses-1")
+            .port(41820, 8080)
+            .volume("/home/u/work", "/home/dev/workspace")
+            .volume_ro("/opt/skel", "/opt/skel")
+            .env("OQTO", "1")
+            .resources(ResourceLimits {
+                memory_mb: Some(512),
+                gpus: Some("0,1".to_string()),
+                ..Default::default()
+            });
+
+        let podman = create_body(RuntimeType::Podman, &config);
+        assert_eq!(
+            podman["HostConfig"]["Binds"],
+            json!([
+                "/home/u/work:/home/dev/workspace:Z",
+                "/opt/skel:/opt/skel:ro,Z"
+            ])
+        );
+        assert_eq!(podman["HostConfig"]["NetworkMode"], "pasta:-m,1500");
+        assert_eq!(
+            podman["HostConfig"]["PortBindings"]["8080/tcp"],
+            json!([{ "HostPort": "41820" }])
+        );
+        assert_eq!(podman["HostConfig"]["MemorySwap"], 512 * 1024 * 1024);
+        assert_eq!(
+            podman["HostConfig"]["Devices"][1]["PathOnHost"],
+            "nvidia.com/gpu=1"
+        );
+        assert_eq!(podman["Hostname"], "ses-1");
+        assert_eq!(podman["Env"], json!(["OQTO=1"]));
+
+        let docker = create_body(RuntimeType::Docker, &config.network_mode("host"));
+        assert_eq!(docker["HostConfig"]["Binds"][1], "/opt/skel:/opt/skel:ro");
+        assert_eq!(docker["HostConfig"]["NetworkMode"], "host");
+        assert_eq!(docker["HostConfig"]["PortBindings"], json!({}));
+        assert_eq!(
+            docker["HostConfig"]["DeviceRequests"][0]["DeviceIDs"],
+            json!(["0", "1"])
+        );
+        assert!(docker.get("Hostname").is_none());
+    }
+}
@@ -0,0 +1,123 @@
+//! Harness crash forensics.
+//!
+//! When a Pi process exits abnormally, the user's runner writes a forensic
+//! bundle (stderr tail, session file tail, core dump details, redacted
+//! environment). The backend attaches each bundle's summary to the chat
+//! session it belongs to and fetches the full bundle from the runner when an
+//! admin downloads it. With object storage configured, full bundles are also
+//! archived there on sync, so they stay downloadable after the runner (or the
+//! node it ran on) is gone.
+
+mod repository;
+
+pub use repository::{CrashBundleQuery, CrashBundleRecord, CrashBundleRepository};
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::Result;
+use tracing::{debug, warn};
+
+use oqto_runner::client::RunnerClient;
+
+use crate::storage::StorageBackend;
+
+/// Time the runner needs after the exit event to write the bundle (it waits
+/// for the process status and for systemd-coredump).
+const COLLECTION_GRACE: Duration = Duration::from_secs(6);
+
+/// Attaches runner crash bundles to session records.
+#[derive(Clone)]
+pub struct CrashBundleService {
+    repo: CrashBundleRepository,
+    storage: Option<Arc<dyn StorageBackend>>,
+}
+
+fn storage_key(user_id: &str, bundle_id: &str) -> String {
+    format!("crash-bundles/{user_id}/{bundle_id}.json")
+}
+
+impl CrashBundleService {
+    pub fn new(repo: CrashBundleRepository) -> Self {
+        Self {
+            repo,
+            storage: None,
+        }
+    }
+
+    /// Archive full bundles in `storage`.
+    pub fn with_storage(mut self, storage: Arc<dyn StorageBackend>) -> Self {
+        self.storage = Some(storage);
+        self
+    }
+
+    pub fn repository(&self) -> &CrashBundleRepository {
+        &self.repo
+    }
+
+    /// Pull bundle summaries from `runner` and record the new ones.
+    pub async fn sync(
+        &self,
+        runner: &RunnerClient,
+        user_id: &str,
+        session_id: Option<&str>,
+    ) -> Result<usize> {
+        let listed = runner.list_crash_bundles(session_id).await?;
+        if let Some(storage) = &self.storage {
+            for summary in &listed.bundles {
+                let key = storage_key(user_id, &summary.id);
+                if let Err(e) = archive_bundle(storage.as_ref(), runner, &key, &summary.id).await {
+                    warn!(user_id = %user_id, bundle_id = %summary.id, "Crash bundle archive failed: {:#}", e);
+                }
+            }
+        }
+        self.repo.record(user_id, &listed.bundles).await
+    }
+
+    /// Archived copy of a bundle (pretty-printed JSON), if any.
+    pub async fn archived(&self, user_id: &str, bundle_id: &str) -> Result<Option<Vec<u8>>> {
+        match &self.storage {
+            Some(storage) => storage.get(&storage_key(user_id, bundle_id)).await,
+            None => Ok(None),
+        }
+    }
+
+    /// Sync a session's bundles once the runner has had time to write them.
+    /// Called when a non-recoverable agent error is forwarded.
+    pub fn schedule_sync(
+        self: &Arc<Self>,
+        runner: RunnerClient,
+        user_id: String,
+        session_id: String,
+    ) {
+        let service = Arc::clone(self);
+        tokio::spawn(async move {
+            tokio::time::sleep(COLLECTION_GRACE).await;
+            match service.sync(&runner, &user_id, Some(&session_id)).await {
+                Ok(0) => debug!(session_id = %session_id, "No crash bundle for agent error"),
+                Ok(n) => warn!(
+                    user_id = %user_id,
+                    session_id = %session_id,
+                    bundles = n,
+                    "Agent harness crashed; crash bundle attached to session"
+                ),
+                Err(e) => debug!(session_id = %session_id, "Crash bundle sync failed: {:#}", e),
+            }
+        });
+    }
+}
+
+async fn archive_bundle(
+    storage: &dyn StorageBackend,
+    runner: &RunnerClient,
+    key: &str,
+    bundle_id: &str,
+) -> Result<()> {
+    if storage.exists(key).await? {
+        return Ok(());
+    }
+    let bundle = runner.get_crash_bundle(bundle_id).await?;
+    storage
+        .put(key, serde_json::to_vec_pretty(&bundle)?, "application/json")
+        .await
+}
@@ -0,0 +1,172 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use sqlx::{FromRow, QueryBuilder, Sqlite, SqlitePool};
+
+use oqto_runner::protocol::CrashBundleSummary;
+
+const BUNDLE_COLUMNS: &str = "id, user_id, session_id, pi_session_id, harness, exit_code, signal, \
+     has_core_dump, last_stderr, collected_at, recorded_at";
+
+/// Crash bundle attached to a chat session.
+#[derive(Debug, Clone, Serialize, FromRow)]
+pub struct CrashBundleRecord {
+    /// Bundle ID on the user's runner.
+    pub id: String,
+    pub user_id: String,
+    pub session_id: String,
+    pub pi_session_id: Option<String>,
+    pub harness: String,
+    pub exit_code: Option<i32>,
+    pub signal: Option<i32>,
+    pub has_core_dump: bool,
+    pub last_stderr: Option<String>,
+    pub collected_at: String,
+    pub recorded_at: String,
+}
+
+/// Filters for listing crash bundles.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct CrashBundleQuery {
+    #[serde(default)]
+    pub user_id: Option<String>,
+    #[serde(default)]
+    pub session_id: Option<String>,
+    #[serde(default)]
+    pub limit: Option<i64>,
+}
+
+#[derive(Debug, Clone)]
+pub struct CrashBundleRepository {
+    pool: SqlitePool,
+}
+
+impl CrashBundleRepository {
+    pub fn new(pool: SqlitePool) -> Self {
+        Self { pool }
+    }
+
+    /// Record runner bundle summaries for `user_id`. Returns how many were
+    /// new.
+    pub async fn record(&self, user_id: &str, bundles: &[CrashBundleSummary]) -> Result<usize> {
+        let mut inserted = 0;
+        for bundle in bundles {
+            let result = sqlx::query(
+                r#"INSERT OR IGNORE INTO session_crash_bundles
+                       (id, user_id, session_id, pi_session_id, harness, exit_code, signal,
+                        has_core_dump, last_stderr, collected_at)
+                   VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?)"#,
+            )
+            .bind(&bundle.id)
+            .bind(user_id)
+            .bind(&bundle.session_id)
+            .bind(&bundle.pi_session_id)
+            .bind(&bundle.harness)
+            .bind(bundle.exit_code)
+            .bind(bundle.signal)
+            .bind(bundle.has_core_dump)
+            .bind(&bundle.last_stderr)
+            .bind(&bundle.collected_at)
+            .execute(&self.pool)
+            .await
+            .context("insert crash bundle")?;
+            inserted += result.rows_affected() as usize;
+        }
+        Ok(inserted)
+    }
+
+    pub async fn get(&self, user_id: &str, id: &str) -> Result<Option<CrashBundleRecord>> {
+        sqlx::query_as::<_, CrashBundleRecord>(&format!(
+            "SELECT {BUNDLE_COLUMNS} FROM session_crash_bundles WHERE user_id = ? AND id = ?"
+        ))
+        .bind(user_id)
+        .bind(id)
+        .fetch_optional(&self.pool)
+        .await
+        .context("get crash bundle")
+    }
+
+    /// Newest first. `session_id` matches the runner or Pi session ID.
+    pub async fn list(&self, query: &CrashBundleQuery) -> Result<Vec<CrashBundleRecord>> {
+        let mut qb = QueryBuilder::<Sqlite>::new(format!(
+            "SELECT {BUNDLE_COLUMNS} FROM session_crash_bundles WHERE 1 = 1"
+        ));
+        if let Some(user_id) = &query.user_id {
+            qb.push(" AND user_id = ").push_bind(user_id.clone());
+        }
+        if let Some(session_id) = &query.session_id {
+            qb.push(" AND (session_id = ")
+                .push_bind(session_id.clone())
+                .push(" OR pi_session_id = ")
+                .push_bind(session_id.clone())
+                .push(")");
+        }
+        qb.push(" ORDER BY collected_at DESC LIMIT ")
+            .push_bind(query.limit.unwrap_or(100).clamp(1, 1000));
+
+        qb.build_query_as::<CrashBundleRecord>()
+            .fetch_all(&self.pool)
+            .await
+            .context("list crash bundles")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::Database;
+
+    fn summary(id: &str, session_id: &str, collected_at: &str) -> CrashBundleSummary {
+        CrashBundleSummary {
+            id: id.to_string(),
+            session_id: session_id.to_string(),
+            pi_session_id: Some(format!("pi-{session_id}")),
+            harness: "pi".to_string(),
+            collected_at: collected_at.to_string(),
+            exit_code: None,
+            signal: Some(11),
+            has_core_dump: true,
+            last_stderr: Some("Segmentation fault".to_string()),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_record_and_list() {
+        let db = Database::in_memory().await.unwrap();
+        sqlx::query("INSERT INTO users (id, username, email, display_name) VALUES (?, ?, ?, ?)")
+            .bind("alice")
+            .bind("alice")
+            .bind("alice@example.com")
+            .bind("Alice")
+            .execute(db.pool())
+            .await
+            .unwrap();
+        let repo = CrashBundleRepository::new(db.pool().clone());
+
+        let bundles = [
+            summary("crash-1", "ses_a", "2026-05-12T10:00:00.000Z"),
+            summary("crash-2", "ses_b", "2026-05-12T11:00:00.000Z"),
+        ];
+        assert_eq!(repo.record("alice", &bundles).await.unwrap(), 2);
+        // Re-syncing the same bundles is a no-op.
+        assert_eq!(repo.record("alice", &bundles).await.unwrap(), 0);
+
+        let all = repo.list(&CrashBundleQuery::default()).await.unwrap();
+        assert_eq!(all.len(), 2);
+        assert_eq!(all[0].id, "crash-2");
+        assert!(all[0].has_core_dump);
+        assert_eq!(all[0].signal, Some(11));
+
+        let by_pi_id = repo
+            .list(&CrashBundleQuery {
+                session_id: Some("pi-ses_a".to_string()),
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+        assert_eq!(by_pi_id.len(), 1);
+        assert_eq!(by_pi_id[0].id, "crash-1");
+
+        assert!(repo.get("alice", "crash-1").await.unwrap().is_some());
+        assert!(repo.get("bob", "crash-1").await.unwrap().is_none());
+    }
+}
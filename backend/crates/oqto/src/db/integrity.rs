@@ -0,0 +1,441 @@
+//! Integrity checks and automatic recovery for the server database.
+//!
+//! At startup `oqto.db` gets a `quick_check` before it is opened; a corrupted
+//! file is moved aside and replaced with the newest snapshot that still passes
+//! the check. While running, a background monitor runs the full
+//! `integrity_check`, snapshots the database when it is healthy and asks each
+//! user's runner to do the same for their hstry database. Anything that goes
+//! wrong is recorded in [`DbHealth`] and surfaced to the frontend as a
+//! degraded-mode banner.
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::RwLock;
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use oqto_history::integrity::{self, IntegrityCheck};
+use serde::{Deserialize, Serialize};
+use sqlx::SqlitePool;
+use tracing::{debug, error, warn};
+
+use crate::api::AppState;
+use crate::runner::router::{ExecutionTarget, resolve_runner_for_target};
+use crate::user::UserListQuery;
+use crate::ws::WsEvent;
+
+/// Health key for the server database.
+const SERVER_DB_KEY: &str = "oqto_db";
+/// Health key for users' hstry databases (aggregated).
+const HISTORY_DB_KEY: &str = "hstry";
+
+/// Database integrity configuration.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DbIntegrityConfig {
+    /// Run integrity checks at startup and on a schedule.
+    pub enabled: bool,
+    /// Hours between scheduled checks (and snapshots).
+    pub check_interval_hours: u64,
+    /// Snapshot directory. Defaults to `backups/` next to the database.
+    pub backup_dir: Option<PathBuf>,
+    /// Number of snapshots to keep per database.
+    pub keep_backups: usize,
+    /// Replace a corrupted database with the newest good snapshot at startup.
+    pub auto_restore: bool,
+    /// Also check each user's hstry database via their runner.
+    pub check_user_history: bool,
+}
+
+impl Default for DbIntegrityConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            check_interval_hours: 6,
+            backup_dir: None,
+            keep_backups: 7,
+            auto_restore: true,
+            check_user_history: true,
+        }
+    }
+}
+
+impl DbIntegrityConfig {
+    fn backup_dir_for(&self, db_path: &Path) -> PathBuf {
+        self.backup_dir.clone().unwrap_or_else(|| {
+            db_path
+                .parent()
+                .unwrap_or_else(|| Path::new("."))
+                .join("backups")
+        })
+    }
+}
+
+/// A detected database problem shown to users while it is unresolved.
+#[derive(Debug, Clone, Serialize)]
+pub struct DegradedNotice {
+    /// Affected database ("oqto_db" or "hstry").
+    pub component: String,
+    /// Human-readable description for the banner.
+    pub message: String,
+    pub detected_at: DateTime<Utc>,
+    /// Snapshot file the database was restored from, if an automatic restore happened.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub restored_from: Option<String>,
+}
+
+/// Shared record of database problems (degraded mode).
+#[derive(Debug, Default)]
+pub struct DbHealth {
+    notices: RwLock<HashMap<String, DegradedNotice>>,
+}
+
+impl DbHealth {
+    /// Record or replace the notice for `key`.
+    pub fn report(&self, key: impl Into<String>, notice: DegradedNotice) {
+        if let Ok(mut notices) = self.notices.write() {
+            notices.insert(key.into(), notice);
+        }
+    }
+
+    /// Drop the notice for `key` once the problem is gone.
+    pub fn clear(&self, key: &str) {
+        if let Ok(mut notices) = self.notices.write() {
+            notices.remove(key);
+        }
+    }
+
+    fn contains(&self, key: &str) -> bool {
+        self.notices
+            .read()
+            .map(|notices| notices.contains_key(key))
+            .unwrap_or(false)
+    }
+
+    /// Current notices, oldest first.
+    pub fn notices(&self) -> Vec<DegradedNotice> {
+        let mut notices: Vec<DegradedNotice> = self
+            .notices
+            .read()
+            .map(|notices| notices.values().cloned().collect())
+            .unwrap_or_default();
+        notices.sort_by_key(|n| n.detected_at);
+        notices
+    }
+
+    pub fn is_degraded(&self) -> bool {
+        self.notices
+            .read()
+            .map(|notices| !notices.is_empty())
+            .unwrap_or(false)
+    }
+}
+
+/// Check the database file before it is opened and restore it if needed.
+///
+/// Never fails startup because of corruption: without a usable snapshot the
+/// corrupted file stays in place and the server runs in degraded mode.
+pub async fn prepare_database(
+    db_path: &Path,
+    config: &DbIntegrityConfig,
+    health: &DbHealth,
+) -> Result<()> {
+    if !config.enabled || !db_path.exists() {
+        return Ok(());
+    }
+
+    let problems = match integrity::check_file(db_path, IntegrityCheck::Quick).await {
+        Ok(problems) => problems,
+        Err(e) => {
+            warn!(
+                "Skipping startup integrity check of {}: {e:#}",
+                db_path.display()
+            );
+            return Ok(());
+        }
+    };
+    if problems.is_empty() {
+        debug!("Startup integrity check passed for {}", db_path.display());
+        return Ok(());
+    }
+    error!(
+        "Database {} failed integrity check: {:?}",
+        db_path.display(),
+        problems
+    );
+
+    let backup_dir = config.backup_dir_for(db_path);
+    let backup = if config.auto_restore {
+        integrity::latest_good_snapshot(&backup_dir, &db_stem(db_path)).await
+    } else {
+        None
+    };
+
+    let Some(backup) = backup else {
+        health.report(
+            SERVER_DB_KEY,
+            DegradedNotice {
+                component: SERVER_DB_KEY.to_string(),
+                message: "The server database failed its integrity check and no usable backup \
+                          was found. Data may be missing or inconsistent until it is repaired."
+                    .to_string(),
+                detected_at: Utc::now(),
+                restored_from: None,
+            },
+        );
+        return Ok(());
+    };
+
+    let quarantined = integrity::quarantine(db_path).context("quarantining corrupted database")?;
+    std::fs::copy(&backup, db_path)
+        .with_context(|| format!("restoring {} from {}", db_path.display(), backup.display()))?;
+    warn!(
+        "Restored {} from {} (corrupted file kept at {})",
+        db_path.display(),
+        backup.display(),
+        quarantined.display()
+    );
+
+    let backup_name = backup
+        .file_name()
+        .map(|n| n.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    health.report(
+        SERVER_DB_KEY,
+        DegradedNotice {
+            component: SERVER_DB_KEY.to_string(),
+            message: format!(
+                "The server database was corrupted and has been restored from backup \
+                 {backup_name}. Changes made after that backup are missing."
+            ),
+            detected_at: Utc::now(),
+            restored_from: Some(backup_name),
+        },
+    );
+    Ok(())
+}
+
+/// Periodically check and snapshot the server database and users' hstry databases.
+pub fn start_integrity_monitor(
+    state: AppState,
+    pool: SqlitePool,
+    db_path: PathBuf,
+    config: DbIntegrityConfig,
+) -> tokio::task::JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(Duration::from_secs(
+            config.check_interval_hours.max(1) * 3600,
+        ));
+        loop {
+            interval.tick().await;
+            check_server_db(&state, &pool, &db_path, &config).await;
+            if config.check_user_history {
+                check_user_history(&state, &config).await;
+            }
+        }
+    })
+}
+
+async fn check_server_db(
+    state: &AppState,
+    pool: &SqlitePool,
+    db_path: &Path,
+    config: &DbIntegrityConfig,
+) {
+    let problems = match integrity::run_check(pool, IntegrityCheck::Full).await {
+        Ok(problems) => problems,
+        Err(e) => {
+            warn!("Database integrity check failed to run: {e:#}");
+            return;
+        }
+    };
+
+    if !problems.is_empty() {
+        error!("Database integrity check found problems: {:?}", problems);
+        // Keep an existing (e.g. "restored from backup") notice rather than
+        // overwriting it with a less specific one.
+        if !state.db_health.contains(SERVER_DB_KEY) {
+            state.db_health.report(
+                SERVER_DB_KEY,
+                DegradedNotice {
+                    component: SERVER_DB_KEY.to_string(),
+                    message: "The server database failed its integrity check. Restart the \
+                              server to restore the most recent good backup."
+                        .to_string(),
+                    detected_at: Utc::now(),
+                    restored_from: None,
+                },
+            );
+        }
+        return;
+    }
+
+    match integrity::snapshot(
+        pool,
+        &config.backup_dir_for(db_path),
+        &db_stem(db_path),
+        config.keep_backups,
+    )
+    .await
+    {
+        Ok(path) => debug!("Database snapshot written to {}", path.display()),
+        Err(e) => warn!("Failed to snapshot database: {e:#}"),
+    }
+}
+
+async fn check_user_history(state: &AppState, config: &DbIntegrityConfig) {
+    let users = match state
+        .users
+        .list_users(UserListQuery {
+            is_active: Some(true),
+            ..Default::default()
+        })
+        .await
+    {
+        Ok(users) => users,
+        Err(e) => {
+            warn!("Failed to list users for hstry integrity check: {e:#}");
+            return;
+        }
+    };
+
+    let mut corrupted = 0usize;
+    for user in users {
+        let runner =
+            match resolve_runner_for_target(state, &user.id, &ExecutionTarget::Personal).await {
+                Ok(Some(runner)) => runner,
+                Ok(None) => continue,
+                Err(e) => {
+                    debug!("Skipping hstry integrity check for {}: {e:#}", user.id);
+                    continue;
+                }
+            };
+
+        let result = match runner
+            .check_history_integrity(true, true, config.keep_backups)
+            .await
+        {
+            Ok(result) => result,
+            Err(e) => {
+                debug!("hstry integrity check unavailable for {}: {e:#}", user.id);
+                continue;
+            }
+        };
+        if result.is_ok() {
+            continue;
+        }
+
+        corrupted += 1;
+        let database = result.database.unwrap_or_else(|| "hstry.db".to_string());
+        error!(
+            "hstry database {} of user {} failed integrity check: {:?}",
+            database, user.id, result.problems
+        );
+        let message = match &result.latest_good_backup {
+            Some(backup) => format!(
+                "Your chat history database ({database}) failed its integrity check. Stop \
+                 hstry and restore it from {backup}."
+            ),
+            None => format!(
+                "Your chat history database ({database}) failed its integrity check and no \
+                 good backup exists."
+            ),
+        };
+        state
+            .ws_hub
+            .send_to_user(
+                &user.id,
+                WsEvent::Notification {
+                    level: "error".to_string(),
+                    title: "Chat history database corrupted".to_string(),
+                    message,
+                    category: "db_integrity.hstry".to_string(),
+                    detail: Some(serde_json::json!({
+                        "database": database,
+                        "problems": result.problems,
+                        "latest_good_backup": result.latest_good_backup,
+                    })),
+                },
+            )
+            .await;
+    }
+
+    if corrupted == 0 {
+        state.db_health.clear(HISTORY_DB_KEY);
+        debug!("hstry integrity check passed for all reachable users");
+        return;
+    }
+    state.db_health.report(
+        HISTORY_DB_KEY,
+        DegradedNotice {
+            component: HISTORY_DB_KEY.to_string(),
+            message: format!(
+                "Chat history databases of {corrupted} user(s) failed their integrity check. \
+                 Affected chat history may be incomplete."
+            ),
+            detected_at: Utc::now(),
+            restored_from: None,
+        },
+    );
+}
+
+fn db_stem(db_path: &Path) -> String {
+    db_path
+        .file_stem()
+        .map(|s| s.to_string_lossy().into_owned())
+        .unwrap_or_else(|| "oqto".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_corrupted_database_is_restored_from_snapshot() {
+        let dir = tempfile::tempdir().unwrap();
+        let db_path = dir.path().join("oqto.db");
+        let config = DbIntegrityConfig::default();
+
+        let db = crate::db::Database::connect(&db_path, &crate::db::DbConfig::default())
+            .await
+            .unwrap();
+        integrity::snapshot(db.pool(), &config.backup_dir_for(&db_path), "oqto", 3)
+            .await
+            .unwrap();
+        db.pool().close().await;
+
+        std::fs::write(&db_path, vec![0x42u8; 8192]).unwrap();
+        let _ = std::fs::remove_file(dir.path().join("oqto.db-wal"));
+        let _ = std::fs::remove_file(dir.path().join("oqto.db-shm"));
+
+        let health = DbHealth::default();
+        prepare_database(&db_path, &config, &health).await.unwrap();
+
+        let notices = health.notices();
+        assert_eq!(notices.len(), 1);
+        assert!(notices[0].restored_from.is_some());
+        assert!(
+            integrity::check_file(&db_path, IntegrityCheck::Quick)
+                .await
+                .unwrap()
+                .is_empty()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_healthy_database_is_untouched() {
+        let dir = tempfile::tempdir().unwrap();
+        let db_path = dir.path().join("oqto.db");
+        let db = crate::db::Database::connect(&db_path, &crate::db::DbConfig::default())
+            .await
+            .unwrap();
+        db.pool().close().await;
+
+        let health = DbHealth::default();
+        prepare_database(&db_path, &DbIntegrityConfig::default(), &health)
+            .await
+            .unwrap();
+        assert!(!health.is_degraded());
+    }
+}
@@ -1,21 +1,133 @@
 //! Database module for session persistence.
+//!
+//! Everything lives in a local SQLite file by default. With `[db] url` set to
+//! a `postgres://` URL, the tables several backend replicas must agree on
+//! move to Postgres, and their repositories take a [`DbPool`]: users,
+//! sessions (with their setups, shares and drafts), agents, invite codes, API
+//! keys, roles, workspace locations, shared workspaces, prompt drafts, git
+//! credentials, resource limits, registrations, message annotations, private
+//! messages, impersonations, template test runs, passkeys, webhook
+//! deliveries, secrets, triggers, schedules and runner placements. The
+//! remaining tables (history caches, tool usage, bookmarks, ...) stay in the
+//! per-replica SQLite file.
 
-use anyhow::{Context, Result};
+mod integrity;
+
+pub use integrity::{
+    DbHealth, DbIntegrityConfig, DegradedNotice, prepare_database, start_integrity_monitor,
+};
+
+use anyhow::{Context, Result, bail};
+use serde::{Deserialize, Serialize};
+use sqlx::postgres::{PgPool, PgPoolOptions};
 use sqlx::sqlite::{SqliteConnectOptions, SqlitePool, SqlitePoolOptions};
+use sqlx::{Pool, QueryBuilder};
 use std::path::Path;
 use std::str::FromStr;
 
+/// `[db]` configuration.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DbConfig {
+    /// `postgres://` URL of the database shared by all replicas. Empty keeps
+    /// everything in the local SQLite file.
+    pub url: String,
+    /// Maximum connections of the Postgres pool.
+    pub max_connections: u32,
+}
+
+impl Default for DbConfig {
+    fn default() -> Self {
+        Self {
+            url: String::new(),
+            max_connections: 10,
+        }
+    }
+}
+
+/// Connection pool of the shared tables.
+///
+/// Queries are written once with `$N` placeholders (which SQLite accepts
+/// too) and run on either backend with [`on_pool!`]. Timestamps are bound
+/// from Rust with [`now`] instead of SQLite's `datetime('now')`.
+#[derive(Debug, Clone)]
+pub enum DbPool {
+    Sqlite(SqlitePool),
+    Postgres(PgPool),
+}
+
+impl DbPool {
+    pub fn is_postgres(&self) -> bool {
+        matches!(self, Self::Postgres(_))
+    }
+}
+
+/// Evaluate `$body` with `$conn` bound to the concrete pool of a [`DbPool`].
+///
+/// The body is expanded once per backend, so query types are inferred for
+/// each of them.
+macro_rules! on_pool {
+    ($pool:expr, |$conn:ident| $body:expr) => {
+        match $pool {
+            $crate::db::DbPool::Sqlite($conn) => $body,
+            $crate::db::DbPool::Postgres($conn) => $body,
+        }
+    };
+}
+pub(crate) use on_pool;
+
+/// Query builder for the backend of `pool`, for use inside [`on_pool!`].
+pub fn query_builder<'args, DB: sqlx::Database>(
+    _pool: &Pool<DB>,
+    sql: impl Into<String>,
+) -> QueryBuilder<'args, DB>
+where
+    DB::Arguments<'args>: Default,
+{
+    QueryBuilder::new(sql)
+}
+
+/// Current UTC time in the stored timestamp format (that of SQLite's
+/// `datetime('now')`).
+pub fn now() -> String {
+    timestamp(chrono::Utc::now())
+}
+
+/// `at` in the stored timestamp format.
+pub fn timestamp(at: chrono::DateTime<chrono::Utc>) -> String {
+    at.format("%Y-%m-%d %H:%M:%S").to_string()
+}
+
+/// Parse a stored timestamp: the `datetime('now')` format or RFC 3339.
+pub fn parse_timestamp(value: &str) -> Option<chrono::DateTime<chrono::Utc>> {
+    chrono::DateTime::parse_from_rfc3339(value)
+        .map(|t| t.with_timezone(&chrono::Utc))
+        .ok()
+        .or_else(|| {
+            chrono::NaiveDateTime::parse_from_str(value, "%Y-%m-%d %H:%M:%S")
+                .ok()
+                .map(|t| t.and_utc())
+        })
+}
+
 /// Database connection pool.
 #[derive(Debug, Clone)]
 pub struct Database {
     pool: SqlitePool,
+    shared: DbPool,
 }
 
 impl Database {
-    /// Create a new database connection.
-    ///
-    /// If the path doesn't exist, a new database will be created.
-    pub async fn new(path: &Path) -> Result<Self> {
+    /// Open the local SQLite database and, with `[db] url` set, the shared
+    /// Postgres database. A missing SQLite file is created.
+    pub async fn connect(path: &Path, config: &DbConfig) -> Result<Self> {
+        let postgres = !config.url.is_empty();
+        if postgres
+            && !(config.url.starts_with("postgres://") || config.url.starts_with("postgresql://"))
+        {
+            bail!("[db] url must be a postgres:// URL");
+        }
+
         // Ensure parent directory exists
         if let Some(parent) = path.parent() {
             std::fs::create_dir_all(parent)
@@ -28,7 +140,9 @@ impl Database {
             .context("parsing database URL")?
             .create_if_missing(true)
             .journal_mode(sqlx::sqlite::SqliteJournalMode::Wal)
-            .busy_timeout(std::time::Duration::from_secs(30));
+            .busy_timeout(std::time::Duration::from_secs(30))
+            // Local tables reference users that live in Postgres.
+            .foreign_keys(!postgres);
 
         let pool = SqlitePoolOptions::new()
             .max_connections(5)
@@ -36,7 +150,22 @@ impl Database {
             .await
             .context("connecting to database")?;
 
-        let db = Self { pool };
+        let shared = if postgres {
+            let pg = PgPoolOptions::new()
+                .max_connections(config.max_connections.max(1))
+                .connect(&config.url)
+                .await
+                .context("connecting to Postgres")?;
+            sqlx::migrate!("./migrations-postgres")
+                .run(&pg)
+                .await
+                .context("running Postgres migrations")?;
+            DbPool::Postgres(pg)
+        } else {
+            DbPool::Sqlite(pool.clone())
+        };
+
+        let db = Self { pool, shared };
         db.run_migrations().await?;
 
         Ok(db)
@@ -55,7 +184,10 @@ impl Database {
             .await
             .context("connecting to in-memory database")?;
 
-        let db = Self { pool };
+        let db = Self {
+            shared: DbPool::Sqlite(pool.clone()),
+            pool,
+        };
         db.run_migrations().await?;
 
         Ok(db)
@@ -70,8 +202,13 @@ impl Database {
         Ok(())
     }
 
-    /// Get a reference to the connection pool.
+    /// Get a reference to the local SQLite connection pool.
     pub fn pool(&self) -> &SqlitePool {
         &self.pool
     }
+
+    /// Pool of the tables shared by all replicas.
+    pub fn shared(&self) -> &DbPool {
+        &self.shared
+    }
 }
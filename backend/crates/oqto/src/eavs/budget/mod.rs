@@ -0,0 +1,363 @@
+//! Per-session spend budgets.
+//!
+//! A background check compares the attributed spend (see [`super::usage`])
+//! of every session with new usage against its budget. Reaching `warn_ratio`
+//! of the budget announces `budget.warning`; reaching the budget announces
+//! `budget.exceeded` and, with `suspend`, the agent is aborted and the
+//! session refuses new prompts until an admin raises its budget.
+
+mod repository;
+
+pub use repository::BudgetRepository;
+
+use repository::SessionSpend;
+
+use std::sync::atomic::{AtomicI64, Ordering};
+
+use anyhow::Result;
+use oqto_protocol::events::EventPayload;
+use serde::{Deserialize, Serialize};
+use tracing::warn;
+
+/// Budget enforcement configuration.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct BudgetConfig {
+    /// Check session spend against budgets. Needs `[usage]` enabled.
+    pub enabled: bool,
+    /// Budget per session in USD. Sessions without a budget of their own
+    /// are unlimited when unset.
+    pub session_limit_usd: Option<f64>,
+    /// Share of the budget at which the session is warned.
+    pub warn_ratio: f64,
+    /// Abort the agent and refuse new prompts once the budget is reached.
+    pub suspend: bool,
+    /// How often spend is checked.
+    pub check_interval_seconds: u64,
+}
+
+impl Default for BudgetConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            session_limit_usd: None,
+            warn_ratio: 0.8,
+            suspend: true,
+            check_interval_seconds: 30,
+        }
+    }
+}
+
+/// Highest budget threshold a session's spend reached.
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize, sqlx::Type,
+)]
+#[serde(rename_all = "snake_case")]
+#[sqlx(rename_all = "snake_case")]
+pub enum BudgetLevel {
+    Ok,
+    Warning,
+    Exceeded,
+}
+
+/// Budget state of a session.
+#[derive(Debug, Clone, Serialize)]
+pub struct SessionBudget {
+    pub session_id: String,
+    pub user_id: String,
+    pub spent_usd: f64,
+    /// Budget in effect; `None` when the session is unlimited.
+    pub limit_usd: Option<f64>,
+    /// Whether `limit_usd` was set for this session rather than configured.
+    pub custom_limit: bool,
+    pub level: BudgetLevel,
+    /// Whether new prompts are refused.
+    pub suspended: bool,
+}
+
+/// A session whose spend crossed a higher threshold.
+#[derive(Debug, Clone)]
+pub struct BudgetCrossing {
+    pub session_id: String,
+    pub user_id: String,
+    pub level: BudgetLevel,
+    pub spent_usd: f64,
+    pub limit_usd: f64,
+    /// Whether the session is suspended as a result.
+    pub suspend: bool,
+}
+
+impl BudgetCrossing {
+    /// The canonical event announcing the crossing.
+    pub fn payload(&self) -> EventPayload {
+        match self.level {
+            BudgetLevel::Exceeded => EventPayload::BudgetExceeded {
+                spent_usd: self.spent_usd,
+                limit_usd: self.limit_usd,
+                suspended: self.suspend,
+            },
+            _ => EventPayload::BudgetWarning {
+                spent_usd: self.spent_usd,
+                limit_usd: self.limit_usd,
+            },
+        }
+    }
+}
+
+/// Session budget service.
+pub struct BudgetService {
+    repo: BudgetRepository,
+    config: BudgetConfig,
+    /// Usage recorded up to this cursor has been checked; -1 before the
+    /// first check.
+    cursor: AtomicI64,
+}
+
+impl BudgetService {
+    pub fn new(repo: BudgetRepository, config: BudgetConfig) -> Self {
+        Self {
+            repo,
+            config,
+            cursor: AtomicI64::new(-1),
+        }
+    }
+
+    pub fn config(&self) -> &BudgetConfig {
+        &self.config
+    }
+
+    /// Check the sessions with usage recorded since the last check and
+    /// record the thresholds they crossed. The first check only sets the
+    /// starting point; sessions over budget before a restart stay suspended
+    /// from their stored level.
+    pub async fn check(&self) -> Result<Vec<BudgetCrossing>> {
+        let until = self.repo.usage_cursor().await?;
+        let after = self.cursor.load(Ordering::SeqCst);
+        if after < 0 || until <= after {
+            self.cursor.store(until, Ordering::SeqCst);
+            return Ok(Vec::new());
+        }
+
+        let mut crossings = Vec::new();
+        for spend in self.repo.changed_since(after, until).await? {
+            if let Some(crossing) = self.crossing(&spend) {
+                self.repo
+                    .set_level(&crossing.session_id, &crossing.user_id, crossing.level)
+                    .await?;
+                crossings.push(crossing);
+            }
+        }
+        self.cursor.store(until, Ordering::SeqCst);
+        Ok(crossings)
+    }
+
+    /// Whether prompts to the session are refused. Errors are logged and
+    /// let the prompt through.
+    pub async fn is_suspended(&self, session_id: &str) -> bool {
+        if !self.config.suspend {
+            return false;
+        }
+        self.repo.is_exceeded(session_id).await.unwrap_or_else(|e| {
+            warn!(session_id = %session_id, "Failed to check session budget: {e:#}");
+            false
+        })
+    }
+
+    /// Budget state of a session, or `None` if it has no recorded spend.
+    pub async fn status(&self, session_id: &str) -> Result<Option<SessionBudget>> {
+        Ok(self
+            .repo
+            .spend(session_id)
+            .await?
+            .map(|spend| self.budget(spend)))
+    }
+
+    /// Sessions that crossed a threshold (or only those at `level`).
+    pub async fn flagged(&self, level: Option<BudgetLevel>) -> Result<Vec<SessionBudget>> {
+        Ok(self
+            .repo
+            .flagged(level)
+            .await?
+            .into_iter()
+            .map(|spend| self.budget(spend))
+            .collect())
+    }
+
+    /// Set (or with `None` reset to the configured default) a session's
+    /// budget and check it right away. `None` if the session has no
+    /// recorded spend.
+    pub async fn set_limit(
+        &self,
+        session_id: &str,
+        limit_usd: Option<f64>,
+    ) -> Result<Option<(SessionBudget, Option<BudgetCrossing>)>> {
+        let Some(spend) = self.repo.spend(session_id).await? else {
+            return Ok(None);
+        };
+        self.repo
+            .set_limit(session_id, &spend.user_id, limit_usd)
+            .await?;
+        let spend = SessionSpend {
+            limit_usd,
+            level: BudgetLevel::Ok,
+            ..spend
+        };
+        let crossing = self.crossing(&spend);
+        let level = match &crossing {
+            Some(crossing) => {
+                self.repo
+                    .set_level(session_id, &spend.user_id, crossing.level)
+                    .await?;
+                crossing.level
+            }
+            None => BudgetLevel::Ok,
+        };
+        Ok(Some((
+            self.budget(SessionSpend { level, ..spend }),
+            crossing,
+        )))
+    }
+
+    fn limit(&self, spend: &SessionSpend) -> Option<f64> {
+        spend.limit_usd.or(self.config.session_limit_usd)
+    }
+
+    /// The threshold `spend` newly crossed, if any.
+    fn crossing(&self, spend: &SessionSpend) -> Option<BudgetCrossing> {
+        let limit = self.limit(spend)?;
+        let level = if spend.spent_usd >= limit {
+            BudgetLevel::Exceeded
+        } else if spend.spent_usd >= limit * self.config.warn_ratio {
+            BudgetLevel::Warning
+        } else {
+            BudgetLevel::Ok
+        };
+        (level > spend.level).then(|| BudgetCrossing {
+            session_id: spend.session_id.clone(),
+            user_id: spend.user_id.clone(),
+            level,
+            spent_usd: spend.spent_usd,
+            limit_usd: limit,
+            suspend: level == BudgetLevel::Exceeded && self.config.suspend,
+        })
+    }
+
+    fn budget(&self, spend: SessionSpend) -> SessionBudget {
+        SessionBudget {
+            limit_usd: self.limit(&spend),
+            custom_limit: spend.limit_usd.is_some(),
+            suspended: spend.level == BudgetLevel::Exceeded && self.config.suspend,
+            session_id: spend.session_id,
+            user_id: spend.user_id,
+            spent_usd: spend.spent_usd,
+            level: spend.level,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::Database;
+
+    async fn record(db: &Database, session_id: &str, message_id: &str, cost_usd: f64) {
+        sqlx::query(
+            r#"INSERT INTO usage_messages
+                   (session_id, message_id, user_id, model, cost_usd, day, created_at)
+               VALUES (?, ?, 'alice', 'claude-sonnet', ?, '2026-06-04', 0)"#,
+        )
+        .bind(session_id)
+        .bind(message_id)
+        .bind(cost_usd)
+        .execute(db.pool())
+        .await
+        .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_thresholds_are_announced_once() {
+        let db = Database::in_memory().await.unwrap();
+        let service = BudgetService::new(
+            BudgetRepository::new(db.pool().clone()),
+            BudgetConfig {
+                enabled: true,
+                session_limit_usd: Some(1.0),
+                ..Default::default()
+            },
+        );
+        record(&db, "ses_1", "m0", 0.5).await;
+        assert!(service.check().await.unwrap().is_empty());
+
+        record(&db, "ses_1", "m1", 0.35).await;
+        record(&db, "ses_2", "m1", 0.1).await;
+        let crossings = service.check().await.unwrap();
+        assert_eq!(crossings.len(), 1);
+        assert_eq!(crossings[0].session_id, "ses_1");
+        assert_eq!(crossings[0].level, BudgetLevel::Warning);
+        assert!(!service.is_suspended("ses_1").await);
+
+        record(&db, "ses_1", "m2", 0.05).await;
+        assert!(service.check().await.unwrap().is_empty());
+
+        record(&db, "ses_1", "m3", 0.2).await;
+        let crossings = service.check().await.unwrap();
+        assert_eq!(crossings[0].level, BudgetLevel::Exceeded);
+        assert!(crossings[0].suspend);
+        assert!(matches!(
+            crossings[0].payload(),
+            EventPayload::BudgetExceeded {
+                suspended: true,
+                ..
+            }
+        ));
+        assert!(service.is_suspended("ses_1").await);
+        assert!(!service.is_suspended("ses_2").await);
+
+        let flagged = service.flagged(None).await.unwrap();
+        assert_eq!(flagged.len(), 1);
+        assert!((flagged[0].spent_usd - 1.1).abs() < 1e-9);
+    }
+
+    #[tokio::test]
+    async fn test_set_limit_lifts_or_applies_suspension() {
+        let db = Database::in_memory().await.unwrap();
+        let service = BudgetService::new(
+            BudgetRepository::new(db.pool().clone()),
+            BudgetConfig {
+                enabled: true,
+                session_limit_usd: Some(1.0),
+                ..Default::default()
+            },
+        );
+        service.check().await.unwrap();
+        record(&db, "ses_1", "m1", 1.5).await;
+        service.check().await.unwrap();
+        assert!(service.is_suspended("ses_1").await);
+
+        let (budget, crossing) = service
+            .set_limit("ses_1", Some(5.0))
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(crossing.is_none());
+        assert!(budget.custom_limit);
+        assert_eq!(budget.level, BudgetLevel::Ok);
+        assert!(!service.is_suspended("ses_1").await);
+
+        let (budget, crossing) = service
+            .set_limit("ses_1", Some(1.8))
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(crossing.unwrap().level, BudgetLevel::Warning);
+        assert_eq!(budget.level, BudgetLevel::Warning);
+
+        assert!(
+            service
+                .set_limit("ses_9", Some(1.0))
+                .await
+                .unwrap()
+                .is_none()
+        );
+    }
+}
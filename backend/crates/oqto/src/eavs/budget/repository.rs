@@ -0,0 +1,156 @@
+use anyhow::{Context, Result};
+use sqlx::{FromRow, QueryBuilder, Sqlite, SqlitePool};
+
+use super::BudgetLevel;
+
+const SPEND_SELECT: &str = r#"SELECT u.session_id AS session_id,
+       MAX(u.user_id) AS user_id,
+       SUM(u.cost_usd) AS spent_usd,
+       b.limit_usd AS limit_usd,
+       COALESCE(b.level, 'ok') AS level
+FROM usage_messages u
+LEFT JOIN session_budgets b ON b.session_id = u.session_id"#;
+
+/// Attributed spend of a session next to its budget state.
+#[derive(Debug, Clone, FromRow)]
+pub struct SessionSpend {
+    pub session_id: String,
+    pub user_id: String,
+    pub spent_usd: f64,
+    /// Session-specific budget, if an admin set one.
+    pub limit_usd: Option<f64>,
+    /// Highest threshold already announced.
+    pub level: BudgetLevel,
+}
+
+#[derive(Debug, Clone)]
+pub struct BudgetRepository {
+    pool: SqlitePool,
+}
+
+impl BudgetRepository {
+    pub fn new(pool: SqlitePool) -> Self {
+        Self { pool }
+    }
+
+    /// Position of the newest recorded message usage. Usage recorded later
+    /// has a higher position.
+    pub async fn usage_cursor(&self) -> Result<i64> {
+        sqlx::query_scalar("SELECT COALESCE(MAX(rowid), 0) FROM usage_messages")
+            .fetch_one(&self.pool)
+            .await
+            .context("query usage cursor")
+    }
+
+    /// Spend of the sessions with usage recorded after `after` up to `until`
+    /// (cursor positions).
+    pub async fn changed_since(&self, after: i64, until: i64) -> Result<Vec<SessionSpend>> {
+        let mut qb = QueryBuilder::<Sqlite>::new(SPEND_SELECT);
+        qb.push(
+            " WHERE u.session_id IN \
+             (SELECT session_id FROM usage_messages WHERE rowid > ",
+        )
+        .push_bind(after)
+        .push(" AND rowid <= ")
+        .push_bind(until)
+        .push(") GROUP BY u.session_id");
+        qb.build_query_as::<SessionSpend>()
+            .fetch_all(&self.pool)
+            .await
+            .context("query changed session spend")
+    }
+
+    /// Spend of one session, or `None` if it has no recorded usage.
+    pub async fn spend(&self, session_id: &str) -> Result<Option<SessionSpend>> {
+        let mut qb = QueryBuilder::<Sqlite>::new(SPEND_SELECT);
+        qb.push(" WHERE u.session_id = ")
+            .push_bind(session_id.to_string())
+            .push(" GROUP BY u.session_id");
+        qb.build_query_as::<SessionSpend>()
+            .fetch_optional(&self.pool)
+            .await
+            .context("query session spend")
+    }
+
+    /// Sessions that crossed a threshold, most recently changed first.
+    /// `level` restricts the list to one level.
+    pub async fn flagged(&self, level: Option<BudgetLevel>) -> Result<Vec<SessionSpend>> {
+        let mut qb = QueryBuilder::<Sqlite>::new(
+            r#"SELECT b.session_id AS session_id,
+                      b.user_id AS user_id,
+                      COALESCE((SELECT SUM(u.cost_usd) FROM usage_messages u
+                                WHERE u.session_id = b.session_id), 0.0) AS spent_usd,
+                      b.limit_usd AS limit_usd,
+                      b.level AS level
+               FROM session_budgets b"#,
+        );
+        match level {
+            Some(level) => qb.push(" WHERE b.level = ").push_bind(level),
+            None => qb.push(" WHERE b.level != 'ok'"),
+        };
+        qb.push(" ORDER BY b.updated_at DESC, b.session_id");
+        qb.build_query_as::<SessionSpend>()
+            .fetch_all(&self.pool)
+            .await
+            .context("list flagged session budgets")
+    }
+
+    /// Whether the session's spend reached its budget.
+    pub async fn is_exceeded(&self, session_id: &str) -> Result<bool> {
+        let level: Option<BudgetLevel> =
+            sqlx::query_scalar("SELECT level FROM session_budgets WHERE session_id = ?")
+                .bind(session_id)
+                .fetch_optional(&self.pool)
+                .await
+                .context("query session budget level")?;
+        Ok(level == Some(BudgetLevel::Exceeded))
+    }
+
+    /// Record the highest threshold a session crossed.
+    pub async fn set_level(
+        &self,
+        session_id: &str,
+        user_id: &str,
+        level: BudgetLevel,
+    ) -> Result<()> {
+        sqlx::query(
+            r#"INSERT INTO session_budgets (session_id, user_id, level)
+               VALUES (?, ?, ?)
+               ON CONFLICT(session_id) DO UPDATE SET
+                   level = excluded.level,
+                   updated_at = datetime('now')"#,
+        )
+        .bind(session_id)
+        .bind(user_id)
+        .bind(level)
+        .execute(&self.pool)
+        .await
+        .context("update session budget level")?;
+        Ok(())
+    }
+
+    /// Set (or with `None` clear) a session-specific budget. Thresholds are
+    /// re-armed so they are announced again against the new budget.
+    pub async fn set_limit(
+        &self,
+        session_id: &str,
+        user_id: &str,
+        limit_usd: Option<f64>,
+    ) -> Result<()> {
+        sqlx::query(
+            r#"INSERT INTO session_budgets (session_id, user_id, limit_usd, level)
+               VALUES (?, ?, ?, 'ok')
+               ON CONFLICT(session_id) DO UPDATE SET
+                   limit_usd = excluded.limit_usd,
+                   level = 'ok',
+                   updated_at = datetime('now')"#,
+        )
+        .bind(session_id)
+        .bind(user_id)
+        .bind(limit_usd)
+        .execute(&self.pool)
+        .await
+        .context("set session budget")?;
+        Ok(())
+    }
+}
@@ -1,6 +1,13 @@
 //! EAVS (LLM Proxy) client module.
 //!
 //! Re-exported from the dedicated `oqto-eavs` crate to keep existing oqto
-//! module paths stable while the god crate is decomposed.
+//! module paths stable while the god crate is decomposed. Spend attribution
+//! per session and model lives in [`usage`], per-session budgets enforced
+//! on top of it in [`budget`].
 
+pub mod budget;
+pub mod usage;
+
+pub use budget::{BudgetConfig, BudgetRepository, BudgetService};
 pub use oqto_eavs::*;
+pub use usage::{UsageConfig, UsageRepository, UsageService};
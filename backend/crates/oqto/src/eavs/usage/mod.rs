@@ -0,0 +1,220 @@
+//! Spend attribution per session and model.
+//!
+//! EAVS meters spend per virtual key, i.e. per user. To break it down by
+//! session and model, the usage agents report on assistant messages
+//! (`stream.message_end`) is recorded as it is forwarded and rolled up on
+//! request.
+
+mod models;
+mod repository;
+
+pub use models::{SessionUsage, UsageConfig, UsageSummary, UsageSummaryQuery};
+pub use repository::UsageRepository;
+
+use models::MessageUsage;
+use repository::{UsageFilter, UsageGroupBy};
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::Result;
+use chrono::{DateTime, NaiveDate, Utc};
+use oqto_protocol::events::{Event, EventPayload};
+use oqto_protocol::messages::Role;
+use tracing::{debug, warn};
+
+/// How often old usage is pruned.
+const MAINTENANCE_INTERVAL: Duration = Duration::from_secs(6 * 3600);
+
+/// Spend attribution service.
+pub struct UsageService {
+    repo: UsageRepository,
+    config: UsageConfig,
+}
+
+impl UsageService {
+    pub fn new(repo: UsageRepository, config: UsageConfig) -> Self {
+        Self { repo, config }
+    }
+
+    pub fn repository(&self) -> &UsageRepository {
+        &self.repo
+    }
+
+    /// Observe a canonical agent event. Only assistant message ends that
+    /// carry usage are recorded.
+    ///
+    /// Persistence happens on a background task so the event stream is never
+    /// blocked on the database.
+    pub fn observe(self: &Arc<Self>, user_id: &str, event: &Event) {
+        let Some(usage) = message_usage(user_id, event) else {
+            return;
+        };
+        let service = Arc::clone(self);
+        tokio::spawn(async move {
+            if let Err(e) = service.repo.record(&usage).await {
+                warn!(
+                    session_id = %usage.session_id,
+                    message_id = %usage.message_id,
+                    "Failed to record message usage: {e:#}"
+                );
+            }
+        });
+    }
+
+    /// Roll up usage between `since` and `until` (inclusive).
+    ///
+    /// `user_id` restricts the summary to one user; `by_user` adds the
+    /// per-user breakdown used by the admin dashboard.
+    pub async fn summary(
+        &self,
+        query: &UsageSummaryQuery,
+        (since, until): (NaiveDate, NaiveDate),
+        user_id: Option<&str>,
+        by_user: bool,
+    ) -> Result<UsageSummary> {
+        let since = since.format("%Y-%m-%d").to_string();
+        let until = until.format("%Y-%m-%d").to_string();
+        let filter = UsageFilter {
+            user_id,
+            session_id: None,
+            since: Some(&since),
+            until: Some(&until),
+        };
+
+        let by_user = if by_user {
+            Some(self.repo.grouped(&filter, UsageGroupBy::User, None).await?)
+        } else {
+            None
+        };
+        Ok(UsageSummary {
+            totals: self.repo.totals(&filter).await?,
+            timeline: self
+                .repo
+                .grouped(&filter, UsageGroupBy::Time(query.bucket), None)
+                .await?,
+            by_model: self
+                .repo
+                .grouped(&filter, UsageGroupBy::Model, None)
+                .await?,
+            by_session: self.repo.top_sessions(&filter, query.limit).await?,
+            by_user,
+            eavs_billed_usd: None,
+            bucket: query.bucket,
+            since,
+            until,
+        })
+    }
+
+    /// Periodically drop usage past the retention period.
+    pub fn start_maintenance_task(self: &Arc<Self>) -> tokio::task::JoinHandle<()> {
+        let service = Arc::clone(self);
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(MAINTENANCE_INTERVAL);
+            loop {
+                interval.tick().await;
+                match service.repo.prune(service.config.retention_days).await {
+                    Ok(0) => {}
+                    Ok(n) => debug!("Pruned {n} message usage records"),
+                    Err(e) => warn!("Failed to prune message usage: {e:#}"),
+                }
+            }
+        })
+    }
+}
+
+/// The usage an assistant `stream.message_end` event reports, if any.
+fn message_usage(user_id: &str, event: &Event) -> Option<MessageUsage> {
+    let EventPayload::StreamMessageEnd { message } = &event.payload else {
+        return None;
+    };
+    if message.role != Role::Assistant {
+        return None;
+    }
+    let usage = message.usage.as_ref()?;
+    let tokens = |n: u64| i64::try_from(n).unwrap_or(i64::MAX);
+    let created_at = if message.created_at > 0 {
+        message.created_at
+    } else {
+        event.ts
+    };
+
+    Some(MessageUsage {
+        session_id: event.session_id.clone(),
+        message_id: message.id.clone(),
+        user_id: user_id.to_string(),
+        model: message
+            .model
+            .clone()
+            .filter(|m| !m.is_empty())
+            .unwrap_or_else(|| "unknown".to_string()),
+        provider: message.provider.clone().unwrap_or_default(),
+        input_tokens: tokens(usage.input_tokens),
+        output_tokens: tokens(usage.output_tokens),
+        cache_read_tokens: usage.cache_read_tokens.map(tokens).unwrap_or(0),
+        cache_write_tokens: usage.cache_write_tokens.map(tokens).unwrap_or(0),
+        cost_usd: usage.cost_usd.unwrap_or(0.0).max(0.0),
+        day: DateTime::from_timestamp_millis(created_at)
+            .unwrap_or_else(Utc::now)
+            .format("%Y-%m-%d")
+            .to_string(),
+        created_at,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use oqto_protocol::messages::{Message, Usage};
+
+    fn message_end(role: Role, usage: Option<Usage>) -> Event {
+        Event {
+            session_id: "ses_1".to_string(),
+            runner_id: "local".to_string(),
+            ts: 1_780_000_000_000,
+            seq: None,
+            payload: EventPayload::StreamMessageEnd {
+                message: Message {
+                    id: "msg_1".to_string(),
+                    idx: 1,
+                    role,
+                    client_id: None,
+                    sender: None,
+                    parts: Vec::new(),
+                    created_at: 1_738_764_000_000,
+                    model: Some("claude-sonnet".to_string()),
+                    provider: Some("anthropic".to_string()),
+                    stop_reason: None,
+                    usage,
+                    tool_call_id: None,
+                    tool_name: None,
+                    is_error: None,
+                    metadata: None,
+                },
+            },
+        }
+    }
+
+    #[test]
+    fn test_message_usage_from_assistant_message_end() {
+        let usage = Usage {
+            input_tokens: 1200,
+            output_tokens: 300,
+            cache_read_tokens: Some(800),
+            cache_write_tokens: None,
+            cost_usd: Some(0.0125),
+        };
+        let recorded =
+            message_usage("alice", &message_end(Role::Assistant, Some(usage.clone()))).unwrap();
+        assert_eq!(recorded.session_id, "ses_1");
+        assert_eq!(recorded.message_id, "msg_1");
+        assert_eq!(recorded.model, "claude-sonnet");
+        assert_eq!(recorded.input_tokens, 1200);
+        assert_eq!(recorded.cache_read_tokens, 800);
+        assert_eq!(recorded.cache_write_tokens, 0);
+        assert_eq!(recorded.day, "2025-02-05");
+
+        assert!(message_usage("alice", &message_end(Role::Assistant, None)).is_none());
+        assert!(message_usage("alice", &message_end(Role::User, Some(usage))).is_none());
+    }
+}
@@ -0,0 +1,196 @@
+use chrono::{Duration, NaiveDate};
+use serde::{Deserialize, Serialize};
+
+/// Days covered by a summary when no `since` is given.
+const DEFAULT_WINDOW_DAYS: i64 = 30;
+
+/// Spend attribution configuration.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct UsageConfig {
+    /// Record per-message token usage and cost from agent events.
+    pub enabled: bool,
+    /// How long per-message usage is kept.
+    pub retention_days: i64,
+}
+
+impl Default for UsageConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            retention_days: 365,
+        }
+    }
+}
+
+/// Usage reported on one assistant message.
+#[derive(Debug, Clone)]
+pub struct MessageUsage {
+    pub session_id: String,
+    pub message_id: String,
+    pub user_id: String,
+    pub model: String,
+    pub provider: String,
+    pub input_tokens: i64,
+    pub output_tokens: i64,
+    pub cache_read_tokens: i64,
+    pub cache_write_tokens: i64,
+    pub cost_usd: f64,
+    /// UTC day (YYYY-MM-DD) the message finished on.
+    pub day: String,
+    /// Unix milliseconds.
+    pub created_at: i64,
+}
+
+/// Width of the time buckets in a usage timeline.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum UsageBucket {
+    #[default]
+    Day,
+    Week,
+    Month,
+}
+
+impl UsageBucket {
+    pub(super) fn expr(self) -> &'static str {
+        match self {
+            Self::Day => "day",
+            Self::Week => "strftime('%Y-W%W', day)",
+            Self::Month => "substr(day, 1, 7)",
+        }
+    }
+}
+
+/// Query parameters for usage summaries.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct UsageSummaryQuery {
+    /// First day to include (YYYY-MM-DD, inclusive). Defaults to 30 days
+    /// before `until`.
+    pub since: Option<String>,
+    /// Last day to include (YYYY-MM-DD, inclusive). Defaults to today (UTC).
+    pub until: Option<String>,
+    #[serde(default)]
+    pub bucket: UsageBucket,
+    /// Restrict to one user (admin endpoint only).
+    pub user_id: Option<String>,
+    /// Maximum number of sessions listed, most expensive first.
+    pub limit: Option<i64>,
+}
+
+impl UsageSummaryQuery {
+    /// The inclusive day range the query covers, relative to `today`.
+    pub fn range(&self, today: NaiveDate) -> Result<(NaiveDate, NaiveDate), String> {
+        let until = match self.until.as_deref() {
+            Some(day) => parse_day(day)?,
+            None => today,
+        };
+        let since = match self.since.as_deref() {
+            Some(day) => parse_day(day)?,
+            None => until - Duration::days(DEFAULT_WINDOW_DAYS - 1),
+        };
+        if since > until {
+            return Err("since must not be after until".to_string());
+        }
+        Ok((since, until))
+    }
+}
+
+fn parse_day(day: &str) -> Result<NaiveDate, String> {
+    NaiveDate::parse_from_str(day, "%Y-%m-%d")
+        .map_err(|_| format!("invalid day '{day}', expected YYYY-MM-DD"))
+}
+
+/// Token counts and spend summed over a set of messages.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct UsageTotals {
+    /// Assistant messages that reported usage.
+    pub messages: i64,
+    pub input_tokens: i64,
+    pub output_tokens: i64,
+    pub cache_read_tokens: i64,
+    pub cache_write_tokens: i64,
+    pub cost_usd: f64,
+}
+
+/// Totals for one value of a grouping dimension.
+#[derive(Debug, Clone, Serialize)]
+pub struct UsageGroup {
+    /// Model (`provider/model`), session ID, user ID or time bucket.
+    pub key: String,
+    #[serde(flatten)]
+    pub totals: UsageTotals,
+}
+
+/// Spend rolled up over a day range.
+#[derive(Debug, Clone, Serialize)]
+pub struct UsageSummary {
+    pub since: String,
+    pub until: String,
+    pub bucket: UsageBucket,
+    pub totals: UsageTotals,
+    /// Totals per time bucket, oldest first. Buckets without usage are omitted.
+    pub timeline: Vec<UsageGroup>,
+    /// Most expensive models first.
+    pub by_model: Vec<UsageGroup>,
+    /// Most expensive sessions first.
+    pub by_session: Vec<UsageGroup>,
+    /// Most expensive users first (admin summaries only).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub by_user: Option<Vec<UsageGroup>>,
+    /// All-time spend EAVS billed to the user's virtual keys, for
+    /// reconciling with the attributed totals. Absent when EAVS is not
+    /// configured or unreachable.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub eavs_billed_usd: Option<f64>,
+}
+
+/// Spend of one session.
+#[derive(Debug, Clone, Serialize)]
+pub struct SessionUsage {
+    pub session_id: String,
+    /// First and last message with usage, in Unix milliseconds.
+    pub first_at: i64,
+    pub last_at: i64,
+    pub totals: UsageTotals,
+    /// Most expensive models first.
+    pub by_model: Vec<UsageGroup>,
+    /// Totals per UTC day, oldest first.
+    pub timeline: Vec<UsageGroup>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_summary_range() {
+        let today = NaiveDate::from_ymd_opt(2026, 6, 2).unwrap();
+        let query = UsageSummaryQuery::default();
+        assert_eq!(
+            query.range(today).unwrap(),
+            (NaiveDate::from_ymd_opt(2026, 5, 4).unwrap(), today)
+        );
+
+        let query = UsageSummaryQuery {
+            since: Some("2026-05-01".to_string()),
+            until: Some("2026-05-31".to_string()),
+            ..Default::default()
+        };
+        let (since, until) = query.range(today).unwrap();
+        assert_eq!(since.to_string(), "2026-05-01");
+        assert_eq!(until.to_string(), "2026-05-31");
+
+        let inverted = UsageSummaryQuery {
+            since: Some("2026-06-01".to_string()),
+            until: Some("2026-05-01".to_string()),
+            ..Default::default()
+        };
+        assert!(inverted.range(today).is_err());
+        let malformed = UsageSummaryQuery {
+            since: Some("06/01/2026".to_string()),
+            ..Default::default()
+        };
+        assert!(malformed.range(today).is_err());
+    }
+}
@@ -0,0 +1,332 @@
+use anyhow::{Context, Result};
+use sqlx::{FromRow, QueryBuilder, Sqlite, SqlitePool};
+
+use super::models::{MessageUsage, SessionUsage, UsageBucket, UsageGroup, UsageTotals};
+
+const DEFAULT_SESSION_LIMIT: i64 = 50;
+const MAX_SESSION_LIMIT: i64 = 500;
+const MAX_GROUPS: i64 = 1000;
+
+const TOTALS_COLUMNS: &str = r#"COUNT(*) AS messages,
+       COALESCE(SUM(input_tokens), 0) AS input_tokens,
+       COALESCE(SUM(output_tokens), 0) AS output_tokens,
+       COALESCE(SUM(cache_read_tokens), 0) AS cache_read_tokens,
+       COALESCE(SUM(cache_write_tokens), 0) AS cache_write_tokens,
+       COALESCE(SUM(cost_usd), 0.0) AS cost_usd"#;
+
+const MODEL_KEY: &str = "CASE WHEN provider = '' THEN model ELSE provider || '/' || model END";
+
+#[derive(Debug, Clone, FromRow)]
+struct UsageGroupRow {
+    group_key: String,
+    messages: i64,
+    input_tokens: i64,
+    output_tokens: i64,
+    cache_read_tokens: i64,
+    cache_write_tokens: i64,
+    cost_usd: f64,
+}
+
+impl From<UsageGroupRow> for UsageGroup {
+    fn from(row: UsageGroupRow) -> Self {
+        Self {
+            key: row.group_key,
+            totals: UsageTotals {
+                messages: row.messages,
+                input_tokens: row.input_tokens,
+                output_tokens: row.output_tokens,
+                cache_read_tokens: row.cache_read_tokens,
+                cache_write_tokens: row.cache_write_tokens,
+                cost_usd: row.cost_usd,
+            },
+        }
+    }
+}
+
+/// Which messages an aggregation covers.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct UsageFilter<'a> {
+    pub user_id: Option<&'a str>,
+    pub session_id: Option<&'a str>,
+    /// First day to include (YYYY-MM-DD, inclusive).
+    pub since: Option<&'a str>,
+    /// Last day to include (YYYY-MM-DD, inclusive).
+    pub until: Option<&'a str>,
+}
+
+impl UsageFilter<'_> {
+    fn push(&self, qb: &mut QueryBuilder<'_, Sqlite>) {
+        qb.push(" WHERE 1 = 1");
+        if let Some(user_id) = self.user_id {
+            qb.push(" AND user_id = ").push_bind(user_id.to_string());
+        }
+        if let Some(session_id) = self.session_id {
+            qb.push(" AND session_id = ")
+                .push_bind(session_id.to_string());
+        }
+        if let Some(since) = self.since {
+            qb.push(" AND day >= ").push_bind(since.to_string());
+        }
+        if let Some(until) = self.until {
+            qb.push(" AND day <= ").push_bind(until.to_string());
+        }
+    }
+}
+
+/// Dimension to group usage by.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UsageGroupBy {
+    Model,
+    Session,
+    User,
+    Time(UsageBucket),
+}
+
+impl UsageGroupBy {
+    fn expr(self) -> &'static str {
+        match self {
+            Self::Model => MODEL_KEY,
+            Self::Session => "session_id",
+            Self::User => "user_id",
+            Self::Time(bucket) => bucket.expr(),
+        }
+    }
+
+    fn order_by(self) -> &'static str {
+        match self {
+            Self::Time(_) => "group_key ASC",
+            _ => "cost_usd DESC, messages DESC",
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct UsageRepository {
+    pool: SqlitePool,
+}
+
+impl UsageRepository {
+    pub fn new(pool: SqlitePool) -> Self {
+        Self { pool }
+    }
+
+    /// Record the usage of one assistant message.
+    ///
+    /// Returns `false` if the message was already recorded (duplicate observation).
+    pub async fn record(&self, usage: &MessageUsage) -> Result<bool> {
+        let result = sqlx::query(
+            r#"
+            INSERT OR IGNORE INTO usage_messages
+                (session_id, message_id, user_id, model, provider, input_tokens, output_tokens,
+                 cache_read_tokens, cache_write_tokens, cost_usd, day, created_at)
+            VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+            "#,
+        )
+        .bind(&usage.session_id)
+        .bind(&usage.message_id)
+        .bind(&usage.user_id)
+        .bind(&usage.model)
+        .bind(&usage.provider)
+        .bind(usage.input_tokens)
+        .bind(usage.output_tokens)
+        .bind(usage.cache_read_tokens)
+        .bind(usage.cache_write_tokens)
+        .bind(usage.cost_usd)
+        .bind(&usage.day)
+        .bind(usage.created_at)
+        .execute(&self.pool)
+        .await
+        .context("insert message usage")?;
+        Ok(result.rows_affected() == 1)
+    }
+
+    /// Totals over all messages matching `filter`.
+    pub async fn totals(&self, filter: &UsageFilter<'_>) -> Result<UsageTotals> {
+        let mut qb = QueryBuilder::<Sqlite>::new(format!(
+            "SELECT '' AS group_key, {TOTALS_COLUMNS} FROM usage_messages"
+        ));
+        filter.push(&mut qb);
+        let row = qb
+            .build_query_as::<UsageGroupRow>()
+            .fetch_one(&self.pool)
+            .await
+            .context("query usage totals")?;
+        Ok(UsageGroup::from(row).totals)
+    }
+
+    /// Totals per value of `group_by`. `limit` is capped at 1000 groups.
+    pub async fn grouped(
+        &self,
+        filter: &UsageFilter<'_>,
+        group_by: UsageGroupBy,
+        limit: Option<i64>,
+    ) -> Result<Vec<UsageGroup>> {
+        let expr = group_by.expr();
+        let mut qb = QueryBuilder::<Sqlite>::new(format!(
+            "SELECT {expr} AS group_key, {TOTALS_COLUMNS} FROM usage_messages"
+        ));
+        filter.push(&mut qb);
+        qb.push(format!(
+            " GROUP BY group_key ORDER BY {} LIMIT ",
+            group_by.order_by()
+        ))
+        .push_bind(limit.unwrap_or(MAX_GROUPS).clamp(1, MAX_GROUPS));
+
+        let rows = qb
+            .build_query_as::<UsageGroupRow>()
+            .fetch_all(&self.pool)
+            .await
+            .context("query grouped usage")?;
+        Ok(rows.into_iter().map(UsageGroup::from).collect())
+    }
+
+    /// The most expensive sessions matching `filter`.
+    pub async fn top_sessions(
+        &self,
+        filter: &UsageFilter<'_>,
+        limit: Option<i64>,
+    ) -> Result<Vec<UsageGroup>> {
+        let limit = limit
+            .unwrap_or(DEFAULT_SESSION_LIMIT)
+            .clamp(1, MAX_SESSION_LIMIT);
+        self.grouped(filter, UsageGroupBy::Session, Some(limit))
+            .await
+    }
+
+    /// Spend of one of the user's sessions, or `None` if it has no recorded usage.
+    pub async fn session(&self, user_id: &str, session_id: &str) -> Result<Option<SessionUsage>> {
+        let (first_at, last_at) = sqlx::query_as::<_, (Option<i64>, Option<i64>)>(
+            r#"SELECT MIN(created_at), MAX(created_at) FROM usage_messages
+               WHERE user_id = ? AND session_id = ?"#,
+        )
+        .bind(user_id)
+        .bind(session_id)
+        .fetch_one(&self.pool)
+        .await
+        .context("query session usage span")?;
+        let (Some(first_at), Some(last_at)) = (first_at, last_at) else {
+            return Ok(None);
+        };
+
+        let filter = UsageFilter {
+            user_id: Some(user_id),
+            session_id: Some(session_id),
+            ..Default::default()
+        };
+        Ok(Some(SessionUsage {
+            session_id: session_id.to_string(),
+            first_at,
+            last_at,
+            totals: self.totals(&filter).await?,
+            by_model: self.grouped(&filter, UsageGroupBy::Model, None).await?,
+            timeline: self
+                .grouped(&filter, UsageGroupBy::Time(UsageBucket::Day), None)
+                .await?,
+        }))
+    }
+
+    /// Drop usage older than `retention_days`.
+    pub async fn prune(&self, retention_days: i64) -> Result<u64> {
+        let result = sqlx::query(r#"DELETE FROM usage_messages WHERE day < date('now', ?)"#)
+            .bind(format!("-{retention_days} days"))
+            .execute(&self.pool)
+            .await
+            .context("prune message usage")?;
+        Ok(result.rows_affected())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::Database;
+
+    fn usage(
+        session_id: &str,
+        message_id: &str,
+        model: &str,
+        day: &str,
+        cost: f64,
+    ) -> MessageUsage {
+        MessageUsage {
+            session_id: session_id.to_string(),
+            message_id: message_id.to_string(),
+            user_id: "alice".to_string(),
+            model: model.to_string(),
+            provider: "anthropic".to_string(),
+            input_tokens: 1000,
+            output_tokens: 200,
+            cache_read_tokens: 0,
+            cache_write_tokens: 0,
+            cost_usd: cost,
+            day: day.to_string(),
+            created_at: 1_780_000_000_000,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_record_and_roll_up() {
+        let db = Database::in_memory().await.unwrap();
+        let repo = UsageRepository::new(db.pool().clone());
+
+        let first = usage("ses_1", "m1", "claude-sonnet", "2026-05-30", 0.5);
+        assert!(repo.record(&first).await.unwrap());
+        assert!(!repo.record(&first).await.unwrap());
+        repo.record(&usage("ses_1", "m2", "claude-haiku", "2026-05-31", 0.25))
+            .await
+            .unwrap();
+        repo.record(&usage("ses_2", "m1", "claude-sonnet", "2026-06-01", 1.0))
+            .await
+            .unwrap();
+        let mut bob = usage("ses_3", "m1", "claude-sonnet", "2026-06-01", 9.0);
+        bob.user_id = "bob".to_string();
+        repo.record(&bob).await.unwrap();
+
+        let alice = UsageFilter {
+            user_id: Some("alice"),
+            ..Default::default()
+        };
+        let totals = repo.totals(&alice).await.unwrap();
+        assert_eq!(totals.messages, 3);
+        assert_eq!(totals.input_tokens, 3000);
+        assert!((totals.cost_usd - 1.75).abs() < 1e-9);
+
+        let by_model = repo
+            .grouped(&alice, UsageGroupBy::Model, None)
+            .await
+            .unwrap();
+        assert_eq!(by_model[0].key, "anthropic/claude-sonnet");
+        assert!((by_model[0].totals.cost_usd - 1.5).abs() < 1e-9);
+
+        let sessions = repo.top_sessions(&alice, None).await.unwrap();
+        assert_eq!(
+            sessions.iter().map(|s| s.key.as_str()).collect::<Vec<_>>(),
+            ["ses_2", "ses_1"]
+        );
+
+        let june = UsageFilter {
+            since: Some("2026-06-01"),
+            ..alice
+        };
+        let monthly = repo
+            .grouped(&june, UsageGroupBy::Time(UsageBucket::Month), None)
+            .await
+            .unwrap();
+        assert_eq!(monthly.len(), 1);
+        assert_eq!(monthly[0].key, "2026-06");
+        assert_eq!(monthly[0].totals.messages, 1);
+
+        let session = repo.session("alice", "ses_1").await.unwrap().unwrap();
+        assert_eq!(session.totals.messages, 2);
+        assert_eq!(session.by_model.len(), 2);
+        assert_eq!(
+            session
+                .timeline
+                .iter()
+                .map(|b| b.key.as_str())
+                .collect::<Vec<_>>(),
+            ["2026-05-30", "2026-05-31"]
+        );
+        assert!(repo.session("bob", "ses_1").await.unwrap().is_none());
+    }
+}
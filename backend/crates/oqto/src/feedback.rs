@@ -1,11 +1,31 @@
-use std::path::PathBuf;
+//! User feedback: a public dropbox the API writes to, synced into an archive.
+//!
+//! With `anonymize` on (the default) only scrubbed copies reach the archive:
+//! user IDs are replaced by salted hashes, the user name is dropped and
+//! e-mail addresses, keys and home directories are redacted from the text.
+//! Each archived item records what was removed in its `provenance`. Raw
+//! items are kept in `<private_archive>/raw/` for `raw_retention_days` so
+//! they can be reprocessed (`oqto feedback reprocess`) after rule changes.
+
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
 use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
 use anyhow::{Context, Result};
 use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
 use tokio::fs;
 use tokio::time;
 
+use crate::redaction::{REDACTOR_VERSION, Redaction, Redactor};
+use crate::storage::StorageBackend;
+
+/// Directory under `private_archive` holding raw items.
+const RAW_DIR: &str = "raw";
+
+/// File under `private_archive` holding the generated user ID salt.
+const SALT_FILE: &str = ".user-id-salt";
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(default)]
 pub struct FeedbackConfig {
@@ -17,6 +37,15 @@ pub struct FeedbackConfig {
     pub keep_public: bool,
     /// Sync interval in seconds.
     pub sync_interval_seconds: u64,
+    /// Archive scrubbed copies instead of raw items.
+    pub anonymize: bool,
+    /// Days raw items (dropbox files and `<private_archive>/raw/`) are kept.
+    /// 0 keeps them forever.
+    pub raw_retention_days: u64,
+    /// Salt for hashing user IDs. Unset generates one and stores it in
+    /// `<private_archive>/.user-id-salt`; set it to get the same hashes on
+    /// every node.
+    pub user_id_salt: Option<String>,
 }
 
 impl Default for FeedbackConfig {
@@ -26,6 +55,9 @@ impl Default for FeedbackConfig {
             private_archive: PathBuf::from("/var/lib/oqto/issue-archive"),
             keep_public: true,
             sync_interval_seconds: 60,
+            anonymize: true,
+            raw_retention_days: 30,
+            user_id_salt: None,
         }
     }
 }
@@ -42,6 +74,106 @@ pub struct FeedbackEntry {
     pub tags: Vec<String>,
 }
 
+/// A feedback entry as archived when anonymization is on.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AnonymizedFeedback {
+    pub id: String,
+    pub title: String,
+    pub body: String,
+    pub created_at: String,
+    /// Salted SHA-256 of the user ID: stable per user, not reversible.
+    pub user_hash: String,
+    pub workspace_path: Option<String>,
+    pub tags: Vec<String>,
+    pub provenance: FeedbackProvenance,
+}
+
+/// What anonymization changed in an entry.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FeedbackProvenance {
+    pub anonymized_at: String,
+    /// Version of the redaction rules applied.
+    pub redactor_version: u32,
+    /// Fields dropped entirely.
+    pub removed_fields: Vec<String>,
+    /// Fields replaced by a hash.
+    pub hashed_fields: Vec<String>,
+    /// Redactions per field and rule.
+    pub redactions: Vec<Redaction>,
+}
+
+/// Scrub `entry` of personal data.
+pub fn anonymize_entry(
+    entry: &FeedbackEntry,
+    redactor: &Redactor,
+    salt: &str,
+) -> AnonymizedFeedback {
+    let mut redactions = Vec::new();
+    let title = redactor.redact("title", &entry.title, &mut redactions);
+    let body = redactor.redact("body", &entry.body, &mut redactions);
+    let workspace_path = entry
+        .workspace_path
+        .as_deref()
+        .map(|path| redactor.redact("workspace_path", path, &mut redactions));
+    let removed_fields = if entry.user_name.is_some() {
+        vec!["user_name".to_string()]
+    } else {
+        Vec::new()
+    };
+
+    AnonymizedFeedback {
+        id: entry.id.clone(),
+        title,
+        body,
+        created_at: entry.created_at.clone(),
+        user_hash: hash_user_id(salt, &entry.user_id),
+        workspace_path,
+        tags: entry.tags.clone(),
+        provenance: FeedbackProvenance {
+            anonymized_at: now_rfc3339(),
+            redactor_version: REDACTOR_VERSION,
+            removed_fields,
+            hashed_fields: vec!["user_id".to_string()],
+            redactions,
+        },
+    }
+}
+
+fn hash_user_id(salt: &str, user_id: &str) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(salt.as_bytes());
+    hasher.update(b":");
+    hasher.update(user_id.as_bytes());
+    hex::encode(&hasher.finalize()[..16])
+}
+
+/// The configured salt, or the one stored under `private_archive`
+/// (generated on first use).
+pub fn load_user_id_salt(config: &FeedbackConfig) -> Result<String> {
+    if let Some(salt) = config.user_id_salt.as_deref().filter(|s| !s.is_empty()) {
+        return Ok(salt.to_string());
+    }
+    let path = config.private_archive.join(SALT_FILE);
+    match std::fs::read_to_string(&path) {
+        Ok(salt) if !salt.trim().is_empty() => return Ok(salt.trim().to_string()),
+        Ok(_) => {}
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+        Err(e) => return Err(e).with_context(|| format!("reading {}", path.display())),
+    }
+    let salt = hex::encode(rand::random::<[u8; 32]>());
+    std::fs::write(&path, &salt).with_context(|| format!("writing {}", path.display()))?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let _ = std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o600));
+    }
+    Ok(salt)
+}
+
+fn raw_dir(config: &FeedbackConfig) -> PathBuf {
+    config.private_archive.join(RAW_DIR)
+}
+
 pub fn ensure_feedback_dirs(config: &FeedbackConfig) -> Result<()> {
     std::fs::create_dir_all(&config.public_dropbox)
         .with_context(|| format!("creating {}", config.public_dropbox.display()))?;
@@ -80,44 +212,319 @@ pub fn now_rfc3339() -> String {
     chrono::Utc::now().to_rfc3339()
 }
 
-pub async fn sync_feedback_loop(config: FeedbackConfig) {
+/// Scrubs entries before they are archived.
+struct Anonymizer {
+    redactor: Redactor,
+    salt: String,
+}
+
+impl Anonymizer {
+    fn new(config: &FeedbackConfig) -> Result<Self> {
+        Ok(Self {
+            redactor: Redactor::new(),
+            salt: load_user_id_salt(config)?,
+        })
+    }
+
+    fn archived_payload(&self, raw: &[u8]) -> Result<Vec<u8>> {
+        let entry: FeedbackEntry = serde_json::from_slice(raw).context("parsing feedback entry")?;
+        let anonymized = anonymize_entry(&entry, &self.redactor, &self.salt);
+        serde_json::to_vec_pretty(&anonymized).context("serializing anonymized feedback")
+    }
+}
+
+/// Copy new dropbox entries to `archive`, under `prefix`, and drop raw items
+/// past their retention.
+///
+/// With the local storage backend the archive is `private_archive`; with a
+/// shared backend entries go to `feedback/` in the object store.
+pub async fn sync_feedback_loop(
+    config: FeedbackConfig,
+    archive: Arc<dyn StorageBackend>,
+    prefix: &'static str,
+) {
+    let anonymizer = if config.anonymize {
+        match Anonymizer::new(&config) {
+            Ok(anonymizer) => Some(anonymizer),
+            Err(err) => {
+                // Never archive raw items when anonymization was asked for.
+                tracing::error!("feedback sync disabled: {:#}", err);
+                return;
+            }
+        }
+    } else {
+        None
+    };
     let mut interval = time::interval(Duration::from_secs(config.sync_interval_seconds));
     loop {
         interval.tick().await;
-        if let Err(err) = sync_feedback_once(&config).await {
+        if let Err(err) =
+            sync_feedback_once(&config, anonymizer.as_ref(), archive.as_ref(), prefix).await
+        {
             tracing::warn!("feedback sync failed: {}", err);
         }
+        if let Err(err) = prune_raw_feedback(&config).await {
+            tracing::warn!("feedback retention cleanup failed: {}", err);
+        }
     }
 }
 
-async fn sync_feedback_once(config: &FeedbackConfig) -> Result<()> {
-    fs::create_dir_all(&config.private_archive)
-        .await
-        .with_context(|| format!("creating {}", config.private_archive.display()))?;
-
-    let mut entries = fs::read_dir(&config.public_dropbox)
-        .await
-        .with_context(|| format!("reading {}", config.public_dropbox.display()))?;
-
+/// JSON files directly in `dir`, with their names.
+async fn json_files(dir: &Path) -> Result<Vec<(PathBuf, String)>> {
+    let mut entries = match fs::read_dir(dir).await {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e).with_context(|| format!("reading {}", dir.display())),
+    };
+    let mut files = Vec::new();
     while let Some(entry) = entries.next_entry().await? {
         let path = entry.path();
-        if path.extension().map(|e| e == "json").unwrap_or(false) {
-            let file_name = match path.file_name() {
-                Some(name) => name.to_owned(),
-                None => continue,
-            };
-            let target = config.private_archive.join(file_name);
-            if target.exists() {
-                continue;
+        if path.extension().map(|e| e == "json").unwrap_or(false)
+            && let Some(file_name) = path.file_name().and_then(|name| name.to_str())
+        {
+            let file_name = file_name.to_string();
+            files.push((path, file_name));
+        }
+    }
+    Ok(files)
+}
+
+async fn sync_feedback_once(
+    config: &FeedbackConfig,
+    anonymizer: Option<&Anonymizer>,
+    archive: &dyn StorageBackend,
+    prefix: &str,
+) -> Result<()> {
+    for (path, file_name) in json_files(&config.public_dropbox).await? {
+        let key = format!("{prefix}{file_name}");
+        if archive.exists(&key).await? {
+            continue;
+        }
+        let raw = fs::read(&path)
+            .await
+            .with_context(|| format!("reading {}", path.display()))?;
+        let payload = match anonymizer {
+            Some(anonymizer) => {
+                let payload = match anonymizer.archived_payload(&raw) {
+                    Ok(payload) => payload,
+                    Err(err) => {
+                        tracing::warn!("skipping feedback {}: {:#}", path.display(), err);
+                        continue;
+                    }
+                };
+                // Keep the raw item privately for reprocessing.
+                let raw_path = raw_dir(config).join(&file_name);
+                fs::create_dir_all(raw_dir(config)).await?;
+                fs::write(&raw_path, &raw)
+                    .await
+                    .with_context(|| format!("writing {}", raw_path.display()))?;
+                payload
             }
-            fs::copy(&path, &target)
+            None => raw,
+        };
+        archive
+            .put(&key, payload, "application/json")
+            .await
+            .with_context(|| format!("archiving {} to {}", path.display(), archive.describe()))?;
+        if !config.keep_public {
+            let _ = fs::remove_file(&path).await;
+        }
+    }
+
+    Ok(())
+}
+
+/// Delete raw items older than `raw_retention_days`. Returns how many.
+pub async fn prune_raw_feedback(config: &FeedbackConfig) -> Result<usize> {
+    if config.raw_retention_days == 0 {
+        return Ok(0);
+    }
+    let retention = Duration::from_secs(config.raw_retention_days * 24 * 60 * 60);
+    let Some(cutoff) = SystemTime::now().checked_sub(retention) else {
+        return Ok(0);
+    };
+    let mut removed = 0;
+    for dir in [config.public_dropbox.clone(), raw_dir(config)] {
+        for (path, _) in json_files(&dir).await? {
+            let modified = fs::metadata(&path).await.and_then(|m| m.modified());
+            if modified.is_ok_and(|modified| modified < cutoff) {
+                fs::remove_file(&path)
+                    .await
+                    .with_context(|| format!("removing {}", path.display()))?;
+                removed += 1;
+            }
+        }
+    }
+    if removed > 0 {
+        tracing::info!("removed {} raw feedback item(s) past retention", removed);
+    }
+    Ok(removed)
+}
+
+/// Outcome of [`reprocess_feedback`].
+#[derive(Debug, Default, Serialize)]
+pub struct ReprocessSummary {
+    pub reprocessed: usize,
+    pub failed: Vec<String>,
+}
+
+/// Anonymize every retained raw item again and overwrite its archived copy,
+/// e.g. after the redaction rules changed.
+pub async fn reprocess_feedback(
+    config: &FeedbackConfig,
+    archive: &dyn StorageBackend,
+    prefix: &str,
+) -> Result<ReprocessSummary> {
+    anyhow::ensure!(
+        config.anonymize,
+        "feedback anonymization is disabled ([feedback] anonymize = false)"
+    );
+    let anonymizer = Anonymizer::new(config)?;
+    let mut summary = ReprocessSummary::default();
+    for (path, file_name) in json_files(&raw_dir(config)).await? {
+        let result = async {
+            let raw = fs::read(&path)
                 .await
-                .with_context(|| format!("copying {} -> {}", path.display(), target.display()))?;
-            if !config.keep_public {
-                let _ = fs::remove_file(&path).await;
+                .with_context(|| format!("reading {}", path.display()))?;
+            let payload = anonymizer.archived_payload(&raw)?;
+            archive
+                .put(&format!("{prefix}{file_name}"), payload, "application/json")
+                .await
+        }
+        .await;
+        match result {
+            Ok(()) => summary.reprocessed += 1,
+            Err(err) => {
+                tracing::warn!("reprocessing feedback {} failed: {:#}", file_name, err);
+                summary.failed.push(file_name);
             }
         }
     }
+    Ok(summary)
+}
 
-    Ok(())
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::LocalStorage;
+
+    fn entry() -> FeedbackEntry {
+        FeedbackEntry {
+            id: "abc".to_string(),
+            title: "Crash for alice@example.com".to_string(),
+            body: "Log at /home/alice/.oqto/log with key sk-abcdefghijklmnopqrstu".to_string(),
+            created_at: "2026-01-01T00:00:00+00:00".to_string(),
+            user_id: "user-1".to_string(),
+            user_name: Some("Alice".to_string()),
+            workspace_path: Some("/home/alice/project".to_string()),
+            tags: vec!["bug".to_string()],
+        }
+    }
+
+    fn config(dir: &Path) -> FeedbackConfig {
+        FeedbackConfig {
+            public_dropbox: dir.join("dropbox"),
+            private_archive: dir.join("archive"),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn anonymized_entries_record_their_redactions() {
+        let anonymized = anonymize_entry(&entry(), &Redactor::new(), "salt");
+        assert_eq!(anonymized.title, "Crash for [redacted:email]");
+        assert_eq!(
+            anonymized.body,
+            "Log at /home/[user]/.oqto/log with key [redacted:api_key]"
+        );
+        assert_eq!(
+            anonymized.workspace_path.as_deref(),
+            Some("/home/[user]/project")
+        );
+        assert_eq!(anonymized.user_hash, hash_user_id("salt", "user-1"));
+        assert_ne!(anonymized.user_hash, hash_user_id("pepper", "user-1"));
+
+        let provenance = &anonymized.provenance;
+        assert_eq!(provenance.removed_fields, vec!["user_name"]);
+        assert_eq!(provenance.hashed_fields, vec!["user_id"]);
+        let rules: Vec<_> = provenance
+            .redactions
+            .iter()
+            .map(|r| (r.field.as_str(), r.rule.as_str()))
+            .collect();
+        assert_eq!(
+            rules,
+            vec![
+                ("title", "email"),
+                ("body", "api_key"),
+                ("body", "home_dir"),
+                ("workspace_path", "home_dir"),
+            ]
+        );
+
+        let json = serde_json::to_string(&anonymized).unwrap();
+        assert!(!json.contains("alice"));
+        assert!(!json.contains("Alice"));
+        assert!(!json.contains("user-1"));
+    }
+
+    #[test]
+    fn generated_salt_is_stable() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = config(dir.path());
+        ensure_feedback_dirs(&config).unwrap();
+        let salt = load_user_id_salt(&config).unwrap();
+        assert_eq!(salt.len(), 64);
+        assert_eq!(load_user_id_salt(&config).unwrap(), salt);
+
+        let configured = FeedbackConfig {
+            user_id_salt: Some("fixed".to_string()),
+            ..config
+        };
+        assert_eq!(load_user_id_salt(&configured).unwrap(), "fixed");
+    }
+
+    #[tokio::test]
+    async fn sync_archives_scrubbed_copies_and_keeps_raw_privately() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = config(dir.path());
+        ensure_feedback_dirs(&config).unwrap();
+        let path = write_feedback_entry(&config, &entry()).await.unwrap();
+        let file_name = path.file_name().unwrap().to_str().unwrap().to_string();
+
+        let archive = LocalStorage::new(dir.path().join("shared"));
+        let anonymizer = Anonymizer::new(&config).unwrap();
+        sync_feedback_once(&config, Some(&anonymizer), &archive, "feedback/")
+            .await
+            .unwrap();
+
+        let archived = archive
+            .get(&format!("feedback/{file_name}"))
+            .await
+            .unwrap()
+            .unwrap();
+        let archived: AnonymizedFeedback = serde_json::from_slice(&archived).unwrap();
+        assert_eq!(archived.id, "abc");
+        assert!(!archived.title.contains("alice@example.com"));
+        assert!(config.private_archive.join("raw").join(&file_name).exists());
+
+        let summary = reprocess_feedback(&config, &archive, "feedback/")
+            .await
+            .unwrap();
+        assert_eq!(summary.reprocessed, 1);
+        assert!(summary.failed.is_empty());
+    }
+
+    #[tokio::test]
+    async fn zero_retention_keeps_raw_items() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = FeedbackConfig {
+            raw_retention_days: 0,
+            ..config(dir.path())
+        };
+        ensure_feedback_dirs(&config).unwrap();
+        write_feedback_entry(&config, &entry()).await.unwrap();
+        assert_eq!(prune_raw_feedback(&config).await.unwrap(), 0);
+        assert_eq!(json_files(&config.public_dropbox).await.unwrap().len(), 1);
+    }
 }
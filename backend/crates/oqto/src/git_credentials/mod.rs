@@ -0,0 +1,68 @@
+//! HTTPS credentials for pushing workspace repositories.
+//!
+//! A user stores one credential (username and access token) per git host.
+//! When they push a workspace through the API, the backend hands their
+//! credentials to the runner, which uses the one matching the remote's
+//! host for that single `git push`. Tokens are never returned by the API.
+
+mod repository;
+
+use serde::{Deserialize, Serialize};
+
+pub use repository::GitCredentialRepository;
+
+/// A stored credential without its token.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct GitCredentialInfo {
+    pub host: String,
+    pub username: String,
+    pub created_at: String,
+    pub updated_at: String,
+}
+
+/// Body of `PUT /git/credentials`.
+#[derive(Debug, Clone, Deserialize)]
+pub struct SetGitCredentialRequest {
+    /// Host of the remote (`github.com`, `git.example.com:8443`). A URL is
+    /// accepted and reduced to its host.
+    pub host: String,
+    pub username: String,
+    /// Password or personal access token.
+    pub token: String,
+}
+
+/// Reduce `host` (or an HTTP(S) URL) to the lowercase `host[:port]` that
+/// credentials are matched against. None when nothing host-like is left.
+pub fn normalize_host(host: &str) -> Option<String> {
+    let host = host.trim();
+    let host = host
+        .strip_prefix("https://")
+        .or_else(|| host.strip_prefix("http://"))
+        .unwrap_or(host);
+    let authority = host.split('/').next().unwrap_or_default();
+    let host = authority
+        .rsplit_once('@')
+        .map_or(authority, |(_, host)| host)
+        .to_ascii_lowercase();
+    let valid = !host.is_empty()
+        && host
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '-' | ':' | '[' | ']'));
+    valid.then_some(host)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_normalize_host() {
+        assert_eq!(normalize_host("GitHub.com").as_deref(), Some("github.com"));
+        assert_eq!(
+            normalize_host("https://me@git.example.com:8443/org/repo.git").as_deref(),
+            Some("git.example.com:8443")
+        );
+        assert_eq!(normalize_host(" "), None);
+        assert_eq!(normalize_host("evil host"), None);
+    }
+}
@@ -0,0 +1,144 @@
+use anyhow::{Context, Result};
+use sqlx::FromRow;
+
+use crate::db::{self, DbPool, on_pool};
+
+use super::GitCredentialInfo;
+
+#[derive(Debug, Clone, FromRow)]
+struct CredentialRow {
+    host: String,
+    username: String,
+    token: String,
+    created_at: String,
+    updated_at: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct GitCredentialRepository {
+    pool: DbPool,
+}
+
+impl GitCredentialRepository {
+    pub fn new(pool: DbPool) -> Self {
+        Self { pool }
+    }
+
+    /// Store the credential of a user for a host, replacing an existing one.
+    pub async fn upsert(
+        &self,
+        user_id: &str,
+        host: &str,
+        username: &str,
+        token: &str,
+    ) -> Result<()> {
+        let now = db::now();
+        on_pool!(&self.pool, |pool| sqlx::query(
+            r#"INSERT INTO git_credentials (user_id, host, username, token, created_at, updated_at)
+               VALUES ($1, $2, $3, $4, $5, $5)
+               ON CONFLICT (user_id, host) DO UPDATE SET
+                   username = excluded.username,
+                   token = excluded.token,
+                   updated_at = excluded.updated_at"#
+        )
+        .bind(user_id)
+        .bind(host)
+        .bind(username)
+        .bind(token)
+        .bind(&now)
+        .execute(pool)
+        .await)
+        .context("upsert git credential")?;
+        Ok(())
+    }
+
+    async fn rows(&self, user_id: &str) -> Result<Vec<CredentialRow>> {
+        on_pool!(&self.pool, |pool| sqlx::query_as::<_, CredentialRow>(
+            "SELECT host, username, token, created_at, updated_at FROM git_credentials WHERE user_id = $1 ORDER BY host"
+        )
+        .bind(user_id)
+        .fetch_all(pool)
+        .await)
+        .context("list git credentials")
+    }
+
+    /// The hosts a user has credentials for.
+    pub async fn list(&self, user_id: &str) -> Result<Vec<GitCredentialInfo>> {
+        Ok(self
+            .rows(user_id)
+            .await?
+            .into_iter()
+            .map(|row| GitCredentialInfo {
+                host: row.host,
+                username: row.username,
+                created_at: row.created_at,
+                updated_at: row.updated_at,
+            })
+            .collect())
+    }
+
+    /// A user's credentials, tokens included, as passed to the runner.
+    pub async fn for_push(
+        &self,
+        user_id: &str,
+    ) -> Result<Vec<oqto_runner::protocol::GitCredential>> {
+        Ok(self
+            .rows(user_id)
+            .await?
+            .into_iter()
+            .map(|row| oqto_runner::protocol::GitCredential {
+                host: row.host,
+                username: row.username,
+                password: row.token,
+            })
+            .collect())
+    }
+
+    pub async fn delete(&self, user_id: &str, host: &str) -> Result<bool> {
+        let deleted = on_pool!(&self.pool, |pool| sqlx::query(
+            "DELETE FROM git_credentials WHERE user_id = $1 AND host = $2"
+        )
+        .bind(user_id)
+        .bind(host)
+        .execute(pool)
+        .await
+        .map(|r| r.rows_affected()))
+        .context("delete git credential")?;
+        Ok(deleted > 0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::Database;
+
+    #[tokio::test]
+    async fn test_credential_lifecycle() {
+        let db = Database::in_memory().await.unwrap();
+        sqlx::query("INSERT INTO users (id, username, email, display_name) VALUES (?, ?, ?, ?)")
+            .bind("alice")
+            .bind("alice")
+            .bind("alice@example.com")
+            .bind("alice")
+            .execute(db.pool())
+            .await
+            .unwrap();
+        let repo = GitCredentialRepository::new(db.shared().clone());
+        assert!(repo.list("alice").await.unwrap().is_empty());
+
+        repo.upsert("alice", "github.com", "alice", "old")
+            .await
+            .unwrap();
+        repo.upsert("alice", "github.com", "alice", "new")
+            .await
+            .unwrap();
+        let listed = repo.list("alice").await.unwrap();
+        assert_eq!(listed.len(), 1);
+        assert_eq!(listed[0].host, "github.com");
+        assert_eq!(repo.for_push("alice").await.unwrap()[0].password, "new");
+
+        assert!(repo.delete("alice", "github.com").await.unwrap());
+        assert!(!repo.delete("alice", "github.com").await.unwrap());
+    }
+}
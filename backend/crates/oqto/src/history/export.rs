@@ -0,0 +1,467 @@
+//! Conversation export: a chat session rendered as one portable document.
+//!
+//! Markdown and HTML exports show messages in order with tool calls, their
+//! results and file edits as diffs; JSON keeps the projected messages as
+//! they are. Attachments (images the agent produced) are embedded as data
+//! URIs, so the document stands on its own.
+
+use chrono::{DateTime, Utc};
+use oqto_runner::protocol::ChatMessageProto;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+/// Longest tool output shown in Markdown and HTML exports.
+const MAX_TOOL_OUTPUT_CHARS: usize = 20_000;
+
+/// Document format of an export.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ExportFormat {
+    #[default]
+    Markdown,
+    Json,
+    Html,
+}
+
+impl ExportFormat {
+    pub fn extension(self) -> &'static str {
+        match self {
+            Self::Markdown => "md",
+            Self::Json => "json",
+            Self::Html => "html",
+        }
+    }
+
+    pub fn content_type(self) -> &'static str {
+        match self {
+            Self::Markdown => "text/markdown; charset=utf-8",
+            Self::Json => "application/json",
+            Self::Html => "text/html; charset=utf-8",
+        }
+    }
+}
+
+/// A file attached to the conversation.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExportAttachment {
+    pub id: String,
+    pub filename: String,
+    pub mime_type: String,
+    pub size_bytes: u64,
+    /// Tool call that produced the file, if known.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tool_call_id: Option<String>,
+    /// Unix ms.
+    pub created_at: i64,
+    /// Backend URL of the file.
+    pub url: String,
+    /// File contents; None when the export's attachment budget ran out.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub data_base64: Option<String>,
+}
+
+impl ExportAttachment {
+    fn data_uri(&self) -> Option<String> {
+        self.data_base64
+            .as_ref()
+            .map(|data| format!("data:{};base64,{}", self.mime_type, data))
+    }
+}
+
+/// A conversation ready to be rendered.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ConversationExport {
+    pub session_id: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub workspace_path: Option<String>,
+    pub exported_at: DateTime<Utc>,
+    pub messages: Vec<ChatMessageProto>,
+    pub attachments: Vec<ExportAttachment>,
+}
+
+impl ConversationExport {
+    pub fn title(&self) -> &str {
+        self.title
+            .as_deref()
+            .filter(|t| !t.trim().is_empty())
+            .unwrap_or(&self.session_id)
+    }
+
+    /// File name for the download, from the title.
+    pub fn file_name(&self, format: ExportFormat) -> String {
+        let slug: String = self
+            .title()
+            .chars()
+            .map(|c| {
+                if c.is_ascii_alphanumeric() {
+                    c.to_ascii_lowercase()
+                } else {
+                    '-'
+                }
+            })
+            .collect();
+        let slug = slug
+            .split('-')
+            .filter(|s| !s.is_empty())
+            .take(8)
+            .collect::<Vec<_>>()
+            .join("-");
+        let slug = if slug.is_empty() { "session" } else { &slug };
+        format!("{slug}.{}", format.extension())
+    }
+
+    pub fn render(&self, format: ExportFormat) -> anyhow::Result<String> {
+        Ok(match format {
+            ExportFormat::Markdown => self.to_markdown(),
+            ExportFormat::Json => serde_json::to_string_pretty(self)?,
+            ExportFormat::Html => self.to_html(),
+        })
+    }
+
+    pub fn to_markdown(&self) -> String {
+        let mut out = format!("# {}\n\n", self.title());
+        out.push_str(&format!("- Session: `{}`\n", self.session_id));
+        if let Some(path) = &self.workspace_path {
+            out.push_str(&format!("- Workspace: `{path}`\n"));
+        }
+        out.push_str(&format!(
+            "- Exported: {}\n",
+            self.exported_at.format("%Y-%m-%d %H:%M UTC")
+        ));
+
+        for message in &self.messages {
+            out.push_str(&format!("\n## {}\n\n", message_heading(message)));
+            for part in &message.parts {
+                render_part(&mut out, part);
+            }
+            for attachment in self.attachments.iter().filter(|a| {
+                a.tool_call_id.is_some()
+                    && message
+                        .parts
+                        .iter()
+                        .any(|p| p.tool_call_id == a.tool_call_id && p.part_type == "tool_call")
+            }) {
+                render_attachment(&mut out, attachment);
+            }
+        }
+
+        let unplaced: Vec<_> = self
+            .attachments
+            .iter()
+            .filter(|a| {
+                !a.tool_call_id.as_ref().is_some_and(|id| {
+                    self.messages.iter().any(|m| {
+                        m.parts.iter().any(|p| {
+                            p.part_type == "tool_call" && p.tool_call_id.as_ref() == Some(id)
+                        })
+                    })
+                })
+            })
+            .collect();
+        if !unplaced.is_empty() {
+            out.push_str("\n## Attachments\n\n");
+            for attachment in unplaced {
+                render_attachment(&mut out, attachment);
+            }
+        }
+        out
+    }
+
+    /// Self-contained HTML page of the Markdown export. Raw HTML in
+    /// messages is escaped.
+    pub fn to_html(&self) -> String {
+        let body = crate::markdown::render_markdown_sync(&self.to_markdown());
+        format!(
+            "<!DOCTYPE html>\n<html lang=\"en\">\n<head>\n<meta charset=\"utf-8\">\n\
+             <title>{}</title>\n<style>{}</style>\n</head>\n<body>\n{}</body>\n</html>\n",
+            crate::markdown::html_escape(self.title()),
+            HTML_STYLE,
+            body
+        )
+    }
+}
+
+const HTML_STYLE: &str = "body{max-width:860px;margin:2rem auto;padding:0 1rem;\
+font-family:system-ui,sans-serif;line-height:1.5;color:#1f2328}\
+h2{border-top:1px solid #d0d7de;padding-top:1rem;font-size:1.1rem}\
+pre{padding:.75rem;overflow-x:auto;border-radius:6px;font-size:.85rem}\
+blockquote{color:#59636e;border-left:3px solid #d0d7de;margin-left:0;padding-left:1rem}\
+img{max-width:100%}";
+
+fn message_heading(message: &ChatMessageProto) -> String {
+    let role = match message.role.as_str() {
+        "user" => "User".to_string(),
+        "assistant" | "agent" => "Assistant".to_string(),
+        "tool" | "toolResult" => "Tool".to_string(),
+        other => {
+            let mut chars = other.chars();
+            chars
+                .next()
+                .map(|c| c.to_uppercase().chain(chars).collect())
+                .unwrap_or_default()
+        }
+    };
+    let model = match (&message.provider_id, &message.model_id) {
+        (Some(provider), Some(model)) => format!(" ({provider}/{model})"),
+        (None, Some(model)) => format!(" ({model})"),
+        _ => String::new(),
+    };
+    let time = DateTime::<Utc>::from_timestamp_millis(message.created_at)
+        .filter(|_| message.created_at > 0)
+        .map(|t| format!(" · {}", t.format("%Y-%m-%d %H:%M:%S UTC")))
+        .unwrap_or_default();
+    format!("{role}{model}{time}")
+}
+
+fn render_part(out: &mut String, part: &oqto_runner::protocol::ChatMessagePartProto) {
+    match part.part_type.as_str() {
+        "text" => {
+            if let Some(text) = &part.text {
+                out.push_str(text.trim_end());
+                out.push_str("\n\n");
+            }
+        }
+        "thinking" => {
+            if let Some(text) = &part.text {
+                out.push_str("> *Thinking*\n>\n");
+                for line in text.trim_end().lines() {
+                    out.push_str("> ");
+                    out.push_str(line);
+                    out.push('\n');
+                }
+                out.push('\n');
+            }
+        }
+        "error" => {
+            out.push_str(&format!(
+                "**Error:** {}\n\n",
+                part.text.as_deref().unwrap_or_default().trim()
+            ));
+        }
+        "tool_call" => {
+            let name = part.tool_name.as_deref().unwrap_or("tool");
+            let input = part.tool_input.as_ref().unwrap_or(&Value::Null);
+            if let Some(diff) = edit_diff(name, input) {
+                out.push_str(&format!("**Edit** `{}`\n\n", file_path(input)));
+                out.push_str(&code_block("diff", &diff));
+            } else if let Some(content) = written_content(name, input) {
+                out.push_str(&format!("**Write** `{}`\n\n", file_path(input)));
+                out.push_str(&code_block("", content));
+            } else if let Some(command) = input.get("command").and_then(Value::as_str) {
+                out.push_str(&format!("**Tool call** `{name}`\n\n"));
+                out.push_str(&code_block("sh", command));
+            } else {
+                out.push_str(&format!("**Tool call** `{name}`\n\n"));
+                let json = serde_json::to_string_pretty(input).unwrap_or_default();
+                out.push_str(&code_block("json", &json));
+            }
+        }
+        "tool_result" => {
+            let output = match &part.tool_output {
+                Some(Value::String(s)) => s.clone(),
+                Some(Value::Null) | None => String::new(),
+                Some(other) => serde_json::to_string_pretty(other).unwrap_or_default(),
+            };
+            let label = if part.tool_status.as_deref() == Some("error") {
+                "Tool error"
+            } else {
+                "Tool result"
+            };
+            match &part.tool_name {
+                Some(name) => out.push_str(&format!("**{label}** `{name}`\n\n")),
+                None => out.push_str(&format!("**{label}**\n\n")),
+            }
+            if !output.trim().is_empty() {
+                out.push_str(&code_block(
+                    "",
+                    &truncate_chars(&output, MAX_TOOL_OUTPUT_CHARS),
+                ));
+            }
+        }
+        _ => {
+            if let Some(text) = &part.text {
+                out.push_str(text.trim_end());
+                out.push_str("\n\n");
+            }
+        }
+    }
+}
+
+fn render_attachment(out: &mut String, attachment: &ExportAttachment) {
+    let alt = attachment.filename.replace(['[', ']'], "");
+    match attachment.data_uri() {
+        Some(uri) if attachment.mime_type.starts_with("image/") => {
+            out.push_str(&format!("![{alt}]({uri})\n\n"));
+        }
+        Some(uri) => out.push_str(&format!("[{alt}]({uri})\n\n")),
+        None => out.push_str(&format!("[{alt}]({})\n\n", attachment.url)),
+    }
+}
+
+fn file_path(input: &Value) -> &str {
+    input
+        .get("path")
+        .or_else(|| input.get("file_path"))
+        .and_then(Value::as_str)
+        .unwrap_or("file")
+}
+
+/// Unified-style diff of an edit tool call.
+fn edit_diff(tool: &str, input: &Value) -> Option<String> {
+    if !tool.eq_ignore_ascii_case("edit") {
+        return None;
+    }
+    let path = file_path(input);
+    let single = [input];
+    let edits: Vec<&Value> = match input.get("edits").and_then(Value::as_array) {
+        Some(edits) => edits.iter().collect(),
+        None => single.to_vec(),
+    };
+    let mut diff = format!("--- a/{path}\n+++ b/{path}\n");
+    let mut any = false;
+    for edit in edits {
+        let old = edit
+            .get("oldText")
+            .or_else(|| edit.get("old_string"))
+            .and_then(Value::as_str);
+        let new = edit
+            .get("newText")
+            .or_else(|| edit.get("new_string"))
+            .and_then(Value::as_str);
+        let (Some(old), Some(new)) = (old, new) else {
+            continue;
+        };
+        any = true;
+        diff.push_str("@@\n");
+        for line in old.lines() {
+            diff.push_str(&format!("-{line}\n"));
+        }
+        for line in new.lines() {
+            diff.push_str(&format!("+{line}\n"));
+        }
+    }
+    any.then_some(diff)
+}
+
+fn written_content<'a>(tool: &str, input: &'a Value) -> Option<&'a str> {
+    if !tool.eq_ignore_ascii_case("write") {
+        return None;
+    }
+    input.get("content").and_then(Value::as_str)
+}
+
+/// Fenced code block whose fence is longer than any backtick run inside.
+fn code_block(lang: &str, content: &str) -> String {
+    let longest = content.split(|c| c != '`').map(str::len).max().unwrap_or(0);
+    let fence = "`".repeat(longest.max(2) + 1);
+    format!(
+        "{fence}{lang}\n{}\n{fence}\n\n",
+        content.trim_end_matches('\n')
+    )
+}
+
+fn truncate_chars(text: &str, max: usize) -> String {
+    match text.char_indices().nth(max) {
+        Some((end, _)) => format!(
+            "{}\n… ({} more characters)",
+            &text[..end],
+            text[end..].chars().count()
+        ),
+        None => text.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use oqto_runner::protocol::ChatMessagePartProto;
+
+    fn part(part_type: &str) -> ChatMessagePartProto {
+        ChatMessagePartProto {
+            id: "p".to_string(),
+            part_type: part_type.to_string(),
+            text: None,
+            text_html: None,
+            tool_name: None,
+            tool_call_id: None,
+            tool_input: None,
+            tool_output: None,
+            tool_status: None,
+            tool_title: None,
+        }
+    }
+
+    fn message(role: &str, parts: Vec<ChatMessagePartProto>) -> ChatMessageProto {
+        serde_json::from_value(serde_json::json!({
+            "id": "m",
+            "session_id": "s1",
+            "role": role,
+            "created_at": 1_767_225_600_000_i64,
+            "parts": [],
+        }))
+        .map(|mut m: ChatMessageProto| {
+            m.parts = parts;
+            m
+        })
+        .unwrap()
+    }
+
+    fn export() -> ConversationExport {
+        let mut ask = part("text");
+        ask.text = Some("Fix the <b>typo</b>".to_string());
+        let mut edit = part("tool_call");
+        edit.tool_name = Some("edit".to_string());
+        edit.tool_call_id = Some("call-1".to_string());
+        edit.tool_input = Some(serde_json::json!({
+            "path": "README.md",
+            "oldText": "teh",
+            "newText": "the",
+        }));
+        let mut result = part("tool_result");
+        result.tool_output = Some(Value::String("```done```".to_string()));
+        ConversationExport {
+            session_id: "s1".to_string(),
+            title: Some("Fix: README typo!".to_string()),
+            workspace_path: None,
+            exported_at: Utc::now(),
+            messages: vec![
+                message("user", vec![ask]),
+                message("assistant", vec![edit, result]),
+            ],
+            attachments: vec![ExportAttachment {
+                id: "a1".to_string(),
+                filename: "plot.png".to_string(),
+                mime_type: "image/png".to_string(),
+                size_bytes: 3,
+                tool_call_id: Some("call-1".to_string()),
+                created_at: 0,
+                url: "/api/sessions/s1/artifacts/a1".to_string(),
+                data_base64: Some("AAAA".to_string()),
+            }],
+        }
+    }
+
+    #[test]
+    fn markdown_shows_edits_as_diffs_and_embeds_attachments() {
+        let export = export();
+        let md = export.to_markdown();
+        assert!(md.starts_with("# Fix: README typo!\n"));
+        assert!(md.contains("## User · 2026-01-01 00:00:00 UTC"));
+        assert!(md.contains("```diff\n--- a/README.md\n+++ b/README.md\n@@\n-teh\n+the\n```"));
+        // Fences outgrow backticks in the content.
+        assert!(md.contains("````\n```done```\n````"));
+        assert!(md.contains("![plot.png](data:image/png;base64,AAAA)"));
+        assert!(!md.contains("## Attachments"));
+        assert_eq!(export.file_name(ExportFormat::Html), "fix-readme-typo.html");
+    }
+
+    #[test]
+    fn html_escapes_message_markup() {
+        let html = export().to_html();
+        assert!(html.starts_with("<!DOCTYPE html>"));
+        assert!(html.contains("&lt;b&gt;typo&lt;/b&gt;"));
+        assert!(!html.contains("<b>typo</b>"));
+    }
+}
@@ -3,6 +3,7 @@
 //! New runtime history features should use `oqto_history::oqto_log` instead.
 
 pub mod canon;
+pub mod export;
 pub mod hstry;
 pub mod models;
 pub mod repository;
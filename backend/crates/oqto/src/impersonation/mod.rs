@@ -0,0 +1,245 @@
+//! Admin impersonation of users.
+//!
+//! An admin asks to act as a user and says why. The user gets a consent
+//! prompt and approves or denies; a request not answered within
+//! `consent_timeout_minutes` lapses. With `allow_break_glass`, an admin can
+//! start right away instead by giving a justification. Once active, the
+//! admin gets a token for the user that carries an `act` claim naming them;
+//! it expires with the impersonation, and either side can end it early.
+//! Everything done with such a token is recorded in the audit log with the
+//! admin and the impersonation, and the user can list it afterwards.
+
+mod models;
+mod repository;
+
+pub use models::{
+    Impersonation, ImpersonationConfig, ImpersonationListQuery, ImpersonationStatus,
+    ImpersonationToken, RequestImpersonation,
+};
+pub use repository::{ImpersonationRepository, NewImpersonation};
+
+use anyhow::{Result, bail};
+use chrono::{Duration, Utc};
+use tracing::info;
+
+/// Impersonation requests, consent and lifetime.
+pub struct ImpersonationService {
+    repo: ImpersonationRepository,
+    config: ImpersonationConfig,
+}
+
+impl ImpersonationService {
+    pub fn new(repo: ImpersonationRepository, config: ImpersonationConfig) -> Result<Self> {
+        if config.consent_timeout_minutes <= 0 {
+            bail!("impersonation.consent_timeout_minutes must be positive");
+        }
+        if config.default_duration_minutes <= 0
+            || config.default_duration_minutes > config.max_duration_minutes
+        {
+            bail!(
+                "impersonation.default_duration_minutes must be positive and at most max_duration_minutes"
+            );
+        }
+        Ok(Self { repo, config })
+    }
+
+    pub fn config(&self) -> &ImpersonationConfig {
+        &self.config
+    }
+
+    pub fn repo(&self) -> &ImpersonationRepository {
+        &self.repo
+    }
+
+    /// Check a request against the policy. Returns the duration it gets, or
+    /// why it is refused.
+    pub fn check_request(&self, request: &RequestImpersonation) -> Result<i64, String> {
+        let reason = request.reason.trim();
+        if reason.is_empty() {
+            return Err("A reason is required; it is shown to the user".to_string());
+        }
+        if request.break_glass {
+            if !self.config.allow_break_glass {
+                return Err("Break-glass impersonation is not allowed".to_string());
+            }
+            if reason.chars().count() < self.config.min_justification_chars {
+                return Err(format!(
+                    "Break-glass impersonation needs a justification of at least {} characters",
+                    self.config.min_justification_chars
+                ));
+            }
+        }
+        let duration = request
+            .duration_minutes
+            .unwrap_or(self.config.default_duration_minutes);
+        if duration <= 0 || duration > self.config.max_duration_minutes {
+            return Err(format!(
+                "duration_minutes must be between 1 and {}",
+                self.config.max_duration_minutes
+            ));
+        }
+        Ok(duration)
+    }
+
+    /// Record a request that passed [`Self::check_request`]: pending until
+    /// the consent deadline, or active right away for break glass.
+    pub async fn request(
+        &self,
+        admin_id: &str,
+        request: &RequestImpersonation,
+        duration_minutes: i64,
+    ) -> Result<Impersonation> {
+        let minutes = if request.break_glass {
+            duration_minutes
+        } else {
+            self.config.consent_timeout_minutes
+        };
+        let impersonation = self
+            .repo
+            .create(&NewImpersonation {
+                admin_id,
+                user_id: &request.user_id,
+                reason: request.reason.trim(),
+                break_glass: request.break_glass,
+                duration_minutes,
+                expires_at: Utc::now() + Duration::minutes(minutes),
+            })
+            .await?;
+        info!(
+            impersonation_id = %impersonation.id,
+            admin_id = %admin_id,
+            user_id = %request.user_id,
+            break_glass = request.break_glass,
+            "Requested impersonation"
+        );
+        Ok(impersonation)
+    }
+
+    /// The user consents: the impersonation runs for its duration from now.
+    /// None if it is not pending any more.
+    pub async fn approve(&self, impersonation: &Impersonation) -> Result<Option<Impersonation>> {
+        let expires_at = Utc::now() + Duration::minutes(impersonation.duration_minutes);
+        if !self
+            .repo
+            .approve(&impersonation.id, &impersonation.user_id, expires_at)
+            .await?
+        {
+            return Ok(None);
+        }
+        info!(impersonation_id = %impersonation.id, "Impersonation approved");
+        self.repo.get(&impersonation.id).await
+    }
+
+    /// The user refuses. None if it is not pending any more.
+    pub async fn deny(&self, impersonation: &Impersonation) -> Result<Option<Impersonation>> {
+        if !self
+            .repo
+            .deny(&impersonation.id, &impersonation.user_id)
+            .await?
+        {
+            return Ok(None);
+        }
+        info!(impersonation_id = %impersonation.id, "Impersonation denied");
+        self.repo.get(&impersonation.id).await
+    }
+
+    /// End a pending or active impersonation early. None if it already
+    /// ended.
+    pub async fn end(
+        &self,
+        impersonation: &Impersonation,
+        ended_by: &str,
+    ) -> Result<Option<Impersonation>> {
+        if !self.repo.end(&impersonation.id, ended_by).await? {
+            return Ok(None);
+        }
+        info!(impersonation_id = %impersonation.id, ended_by = %ended_by, "Impersonation ended");
+        self.repo.get(&impersonation.id).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn service(config: ImpersonationConfig) -> Result<ImpersonationService> {
+        let db = crate::db::Database::in_memory().await.unwrap();
+        ImpersonationService::new(ImpersonationRepository::new(db.shared().clone()), config)
+    }
+
+    fn request(reason: &str, duration: Option<i64>, break_glass: bool) -> RequestImpersonation {
+        RequestImpersonation {
+            user_id: "alice".to_string(),
+            reason: reason.to_string(),
+            duration_minutes: duration,
+            break_glass,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_check_request_applies_policy() {
+        let strict = service(ImpersonationConfig::default()).await.unwrap();
+        assert_eq!(
+            strict.check_request(&request("Ticket 42", None, false)),
+            Ok(30)
+        );
+        assert_eq!(
+            strict.check_request(&request("Ticket 42", Some(90), false)),
+            Ok(90)
+        );
+        assert!(strict.check_request(&request("  ", None, false)).is_err());
+        assert!(
+            strict
+                .check_request(&request("Ticket 42", Some(121), false))
+                .is_err()
+        );
+        assert!(
+            strict
+                .check_request(&request("Ticket 42", Some(0), false))
+                .is_err()
+        );
+        // Break glass is off by default.
+        let justification = "User locked out during an outage, ticket 42";
+        assert!(
+            strict
+                .check_request(&request(justification, None, true))
+                .is_err()
+        );
+
+        let lenient = service(ImpersonationConfig {
+            allow_break_glass: true,
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+        assert_eq!(
+            lenient.check_request(&request(justification, None, true)),
+            Ok(30)
+        );
+        assert!(
+            lenient
+                .check_request(&request("Ticket 42", None, true))
+                .is_err()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_config_is_validated() {
+        assert!(
+            service(ImpersonationConfig {
+                default_duration_minutes: 240,
+                ..Default::default()
+            })
+            .await
+            .is_err()
+        );
+        assert!(
+            service(ImpersonationConfig {
+                consent_timeout_minutes: 0,
+                ..Default::default()
+            })
+            .await
+            .is_err()
+        );
+    }
+}
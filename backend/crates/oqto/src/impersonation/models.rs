@@ -0,0 +1,124 @@
+use serde::{Deserialize, Serialize};
+
+/// `[impersonation]` configuration.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ImpersonationConfig {
+    /// Let admins ask to act as users.
+    pub enabled: bool,
+    /// Minutes a user has to answer a request.
+    pub consent_timeout_minutes: i64,
+    /// Length of an impersonation when the request does not give one.
+    pub default_duration_minutes: i64,
+    /// Longest impersonation an admin may ask for.
+    pub max_duration_minutes: i64,
+    /// Let admins start without the user's consent (break glass). The user
+    /// is still told, and sees afterwards what was done.
+    pub allow_break_glass: bool,
+    /// Shortest justification a break-glass start is accepted with.
+    pub min_justification_chars: usize,
+}
+
+impl Default for ImpersonationConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            consent_timeout_minutes: 15,
+            default_duration_minutes: 30,
+            max_duration_minutes: 120,
+            allow_break_glass: false,
+            min_justification_chars: 20,
+        }
+    }
+}
+
+/// Where an impersonation stands.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ImpersonationStatus {
+    /// Waiting for the user's consent.
+    Pending,
+    /// The admin may act as the user until `expires_at`.
+    Active,
+    /// The user said no.
+    Denied,
+    /// Ended early by the admin or the user.
+    Ended,
+    /// Not answered in time, or ran out.
+    Expired,
+}
+
+impl ImpersonationStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ImpersonationStatus::Pending => "pending",
+            ImpersonationStatus::Active => "active",
+            ImpersonationStatus::Denied => "denied",
+            ImpersonationStatus::Ended => "ended",
+            ImpersonationStatus::Expired => "expired",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "pending" => Some(ImpersonationStatus::Pending),
+            "active" => Some(ImpersonationStatus::Active),
+            "denied" => Some(ImpersonationStatus::Denied),
+            "ended" => Some(ImpersonationStatus::Ended),
+            "expired" => Some(ImpersonationStatus::Expired),
+            _ => None,
+        }
+    }
+}
+
+/// An admin's request to act as a user, and what became of it.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Impersonation {
+    pub id: String,
+    pub admin_id: String,
+    pub user_id: String,
+    /// Why the admin needs access; shown to the user.
+    pub reason: String,
+    /// Started without the user's consent.
+    pub break_glass: bool,
+    pub status: ImpersonationStatus,
+    pub duration_minutes: i64,
+    pub requested_at: String,
+    pub started_at: Option<String>,
+    /// Consent deadline while pending, end of the impersonation once active.
+    pub expires_at: String,
+    pub ended_at: Option<String>,
+    /// Who ended it or denied it.
+    pub ended_by: Option<String>,
+}
+
+/// Body of an impersonation request.
+#[derive(Debug, Clone, Deserialize)]
+pub struct RequestImpersonation {
+    /// User to act as.
+    pub user_id: String,
+    pub reason: String,
+    #[serde(default)]
+    pub duration_minutes: Option<i64>,
+    /// Start right away without consent, when policy allows it.
+    #[serde(default)]
+    pub break_glass: bool,
+}
+
+/// Filters of the admin impersonation list.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct ImpersonationListQuery {
+    pub user_id: Option<String>,
+    pub admin_id: Option<String>,
+    pub status: Option<ImpersonationStatus>,
+    pub limit: Option<i64>,
+}
+
+/// A token to act as the user with, valid until the impersonation ends.
+#[derive(Debug, Clone, Serialize)]
+pub struct ImpersonationToken {
+    pub token: String,
+    /// Unix timestamp.
+    pub expires_at: i64,
+    pub impersonation: Impersonation,
+}
@@ -0,0 +1,401 @@
+use anyhow::{Context, Result};
+use sqlx::FromRow;
+
+use crate::db::{self, DbPool, on_pool};
+
+use super::{Impersonation, ImpersonationListQuery, ImpersonationStatus};
+
+const IMPERSONATION_COLUMNS: &str = "id, admin_id, user_id, reason, break_glass, status, \
+     duration_minutes, requested_at, started_at, expires_at, ended_at, ended_by";
+
+/// Rows that have not been answered, denied or ended, but whose deadline
+/// is still ahead.
+const OPEN: &str = "status IN ('pending', 'active') AND expires_at > $2";
+
+/// Most impersonations in one list.
+const MAX_LIST: i64 = 500;
+
+#[derive(Debug, Clone, FromRow)]
+struct ImpersonationRow {
+    id: String,
+    admin_id: String,
+    user_id: String,
+    reason: String,
+    break_glass: bool,
+    status: String,
+    duration_minutes: i64,
+    requested_at: String,
+    started_at: Option<String>,
+    expires_at: String,
+    ended_at: Option<String>,
+    ended_by: Option<String>,
+}
+
+impl ImpersonationRow {
+    /// Pending and active rows past their deadline read as expired.
+    fn into_impersonation(self, now: &str) -> Impersonation {
+        let status = match ImpersonationStatus::parse(&self.status) {
+            Some(ImpersonationStatus::Pending | ImpersonationStatus::Active)
+                if self.expires_at.as_str() <= now =>
+            {
+                ImpersonationStatus::Expired
+            }
+            Some(status) => status,
+            // The column is constrained to the known values.
+            None => ImpersonationStatus::Ended,
+        };
+        Impersonation {
+            id: self.id,
+            admin_id: self.admin_id,
+            user_id: self.user_id,
+            reason: self.reason,
+            break_glass: self.break_glass,
+            status,
+            duration_minutes: self.duration_minutes,
+            requested_at: self.requested_at,
+            started_at: self.started_at,
+            expires_at: self.expires_at,
+            ended_at: self.ended_at,
+            ended_by: self.ended_by,
+        }
+    }
+}
+
+/// A new impersonation: pending until `expires_at`, or active until then
+/// when started without consent.
+#[derive(Debug, Clone)]
+pub struct NewImpersonation<'a> {
+    pub admin_id: &'a str,
+    pub user_id: &'a str,
+    pub reason: &'a str,
+    pub break_glass: bool,
+    pub duration_minutes: i64,
+    pub expires_at: chrono::DateTime<chrono::Utc>,
+}
+
+#[derive(Debug, Clone)]
+pub struct ImpersonationRepository {
+    pool: DbPool,
+}
+
+impl ImpersonationRepository {
+    pub fn new(pool: DbPool) -> Self {
+        Self { pool }
+    }
+
+    fn generate_id() -> String {
+        format!("imp_{}", nanoid::nanoid!(12))
+    }
+
+    fn collect(rows: Vec<ImpersonationRow>) -> Vec<Impersonation> {
+        let now = db::now();
+        rows.into_iter()
+            .map(|row| row.into_impersonation(&now))
+            .collect()
+    }
+
+    pub async fn create(&self, new: &NewImpersonation<'_>) -> Result<Impersonation> {
+        let id = Self::generate_id();
+        let now = db::now();
+        let (status, started_at) = if new.break_glass {
+            ("active", Some(now.as_str()))
+        } else {
+            ("pending", None)
+        };
+        on_pool!(&self.pool, |pool| sqlx::query(
+            "INSERT INTO impersonations \
+                 (id, admin_id, user_id, reason, break_glass, status, duration_minutes, \
+                  requested_at, started_at, expires_at) \
+             VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)"
+        )
+        .bind(&id)
+        .bind(new.admin_id)
+        .bind(new.user_id)
+        .bind(new.reason)
+        .bind(new.break_glass)
+        .bind(status)
+        .bind(new.duration_minutes)
+        .bind(&now)
+        .bind(started_at)
+        .bind(db::timestamp(new.expires_at))
+        .execute(pool)
+        .await)
+        .context("insert impersonation")?;
+
+        self.get(&id)
+            .await?
+            .ok_or_else(|| anyhow::anyhow!("Impersonation not found after creation"))
+    }
+
+    pub async fn get(&self, id: &str) -> Result<Option<Impersonation>> {
+        let sql = format!("SELECT {IMPERSONATION_COLUMNS} FROM impersonations WHERE id = $1");
+        let row = on_pool!(&self.pool, |pool| sqlx::query_as::<_, ImpersonationRow>(
+            &sql
+        )
+        .bind(id)
+        .fetch_optional(pool)
+        .await)
+        .context("get impersonation")?;
+        Ok(row.map(|row| row.into_impersonation(&db::now())))
+    }
+
+    /// The user's pending or active impersonation, if any.
+    pub async fn open_for_user(&self, user_id: &str) -> Result<Option<Impersonation>> {
+        let sql = format!(
+            "SELECT {IMPERSONATION_COLUMNS} FROM impersonations \
+             WHERE user_id = $1 AND {OPEN} ORDER BY requested_at DESC LIMIT 1"
+        );
+        let row = on_pool!(&self.pool, |pool| sqlx::query_as::<_, ImpersonationRow>(
+            &sql
+        )
+        .bind(user_id)
+        .bind(db::now())
+        .fetch_optional(pool)
+        .await)
+        .context("get open impersonation")?;
+        Ok(row.map(|row| row.into_impersonation(&db::now())))
+    }
+
+    /// Impersonations matching `query`, newest first.
+    pub async fn list(&self, query: &ImpersonationListQuery) -> Result<Vec<Impersonation>> {
+        let mut sql = format!("SELECT {IMPERSONATION_COLUMNS} FROM impersonations WHERE 1 = 1");
+        let mut binds = Vec::new();
+        let mut push = |clause: &str, value: String| {
+            binds.push(value);
+            sql.push_str(&format!(" AND {clause} ${}", binds.len()));
+        };
+        if let Some(user_id) = &query.user_id {
+            push("user_id =", user_id.clone());
+        }
+        if let Some(admin_id) = &query.admin_id {
+            push("admin_id =", admin_id.clone());
+        }
+        match query.status {
+            None => {}
+            Some(ImpersonationStatus::Expired) => {
+                push(
+                    "status IN ('pending', 'active') AND expires_at <=",
+                    db::now(),
+                );
+            }
+            Some(status @ (ImpersonationStatus::Pending | ImpersonationStatus::Active)) => {
+                push("status =", status.as_str().to_string());
+                push("expires_at >", db::now());
+            }
+            Some(status) => push("status =", status.as_str().to_string()),
+        }
+        let limit = query.limit.unwrap_or(100).clamp(1, MAX_LIST);
+        sql.push_str(&format!(
+            " ORDER BY requested_at DESC, id DESC LIMIT {limit}"
+        ));
+
+        let rows = on_pool!(&self.pool, |pool| {
+            let mut q = sqlx::query_as::<_, ImpersonationRow>(&sql);
+            for value in &binds {
+                q = q.bind(value);
+            }
+            q.fetch_all(pool).await
+        })
+        .context("list impersonations")?;
+        Ok(Self::collect(rows))
+    }
+
+    /// Start a pending impersonation the user consented to, until
+    /// `expires_at`. False if it is not pending any more.
+    pub async fn approve(
+        &self,
+        id: &str,
+        user_id: &str,
+        expires_at: chrono::DateTime<chrono::Utc>,
+    ) -> Result<bool> {
+        let updated = on_pool!(&self.pool, |pool| sqlx::query(
+            "UPDATE impersonations SET status = 'active', started_at = $2, expires_at = $3 \
+             WHERE id = $1 AND user_id = $4 AND status = 'pending' AND expires_at > $2"
+        )
+        .bind(id)
+        .bind(db::now())
+        .bind(db::timestamp(expires_at))
+        .bind(user_id)
+        .execute(pool)
+        .await
+        .map(|r| r.rows_affected()))
+        .context("approve impersonation")?;
+        Ok(updated > 0)
+    }
+
+    /// Refuse a pending impersonation. False if it is not pending any more.
+    pub async fn deny(&self, id: &str, user_id: &str) -> Result<bool> {
+        let updated = on_pool!(&self.pool, |pool| sqlx::query(
+            "UPDATE impersonations SET status = 'denied', ended_at = $2, ended_by = $3 \
+             WHERE id = $1 AND user_id = $3 AND status = 'pending' AND expires_at > $2"
+        )
+        .bind(id)
+        .bind(db::now())
+        .bind(user_id)
+        .execute(pool)
+        .await
+        .map(|r| r.rows_affected()))
+        .context("deny impersonation")?;
+        Ok(updated > 0)
+    }
+
+    /// End a pending or active impersonation early. False if it already
+    /// ended.
+    pub async fn end(&self, id: &str, ended_by: &str) -> Result<bool> {
+        let sql = format!(
+            "UPDATE impersonations SET status = 'ended', ended_at = $2, ended_by = $3 \
+             WHERE id = $1 AND {OPEN}"
+        );
+        let updated = on_pool!(&self.pool, |pool| sqlx::query(&sql)
+            .bind(id)
+            .bind(db::now())
+            .bind(ended_by)
+            .execute(pool)
+            .await
+            .map(|r| r.rows_affected()))
+        .context("end impersonation")?;
+        Ok(updated > 0)
+    }
+
+    /// Whether tokens of impersonation `id` may still act as `user_id`.
+    pub async fn is_active(&self, id: &str, user_id: &str) -> Result<bool> {
+        let count: i64 = on_pool!(&self.pool, |pool| sqlx::query_scalar(
+            "SELECT COUNT(*) FROM impersonations \
+             WHERE id = $1 AND status = 'active' AND expires_at > $2 AND user_id = $3"
+        )
+        .bind(id)
+        .bind(db::now())
+        .bind(user_id)
+        .fetch_one(pool)
+        .await)
+        .context("check impersonation")?;
+        Ok(count > 0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::Database;
+    use chrono::{Duration, Utc};
+
+    async fn repo() -> ImpersonationRepository {
+        let db = Database::in_memory().await.unwrap();
+        for user in ["admin", "alice"] {
+            sqlx::query(
+                "INSERT INTO users (id, username, email, display_name) VALUES (?, ?, ?, ?)",
+            )
+            .bind(user)
+            .bind(user)
+            .bind(format!("{user}@example.com"))
+            .bind(user)
+            .execute(db.pool())
+            .await
+            .unwrap();
+        }
+        ImpersonationRepository::new(db.shared().clone())
+    }
+
+    fn request(break_glass: bool, expires_at: chrono::DateTime<Utc>) -> NewImpersonation<'static> {
+        NewImpersonation {
+            admin_id: "admin",
+            user_id: "alice",
+            reason: "Help with a stuck session",
+            break_glass,
+            duration_minutes: 30,
+            expires_at,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_consent_flow() {
+        let repo = repo().await;
+        let pending = repo
+            .create(&request(false, Utc::now() + Duration::minutes(15)))
+            .await
+            .unwrap();
+        assert_eq!(pending.status, ImpersonationStatus::Pending);
+        assert!(!repo.is_active(&pending.id, "alice").await.unwrap());
+        assert_eq!(
+            repo.open_for_user("alice").await.unwrap().unwrap().id,
+            pending.id
+        );
+
+        // Only the impersonated user can consent.
+        let until = Utc::now() + Duration::minutes(30);
+        assert!(!repo.approve(&pending.id, "admin", until).await.unwrap());
+        assert!(repo.approve(&pending.id, "alice", until).await.unwrap());
+        assert!(!repo.approve(&pending.id, "alice", until).await.unwrap());
+        assert!(repo.is_active(&pending.id, "alice").await.unwrap());
+        assert!(!repo.is_active(&pending.id, "admin").await.unwrap());
+
+        assert!(repo.end(&pending.id, "alice").await.unwrap());
+        assert!(!repo.end(&pending.id, "alice").await.unwrap());
+        let ended = repo.get(&pending.id).await.unwrap().unwrap();
+        assert_eq!(ended.status, ImpersonationStatus::Ended);
+        assert_eq!(ended.ended_by.as_deref(), Some("alice"));
+        assert!(!repo.is_active(&pending.id, "alice").await.unwrap());
+        assert!(repo.open_for_user("alice").await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_deny_break_glass_and_expiry() {
+        let repo = repo().await;
+        let pending = repo
+            .create(&request(false, Utc::now() + Duration::minutes(15)))
+            .await
+            .unwrap();
+        assert!(repo.deny(&pending.id, "alice").await.unwrap());
+        let denied = repo.get(&pending.id).await.unwrap().unwrap();
+        assert_eq!(denied.status, ImpersonationStatus::Denied);
+        assert!(
+            !repo
+                .approve(&pending.id, "alice", Utc::now() + Duration::minutes(30))
+                .await
+                .unwrap()
+        );
+
+        let forced = repo
+            .create(&request(true, Utc::now() + Duration::minutes(30)))
+            .await
+            .unwrap();
+        assert_eq!(forced.status, ImpersonationStatus::Active);
+        assert!(forced.started_at.is_some());
+        assert!(repo.is_active(&forced.id, "alice").await.unwrap());
+
+        let lapsed = repo
+            .create(&request(true, Utc::now() - Duration::minutes(1)))
+            .await
+            .unwrap();
+        assert_eq!(lapsed.status, ImpersonationStatus::Expired);
+        assert!(!repo.is_active(&lapsed.id, "alice").await.unwrap());
+
+        let expired = repo
+            .list(&ImpersonationListQuery {
+                status: Some(ImpersonationStatus::Expired),
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+        assert_eq!(expired.len(), 1);
+        assert_eq!(expired[0].id, lapsed.id);
+
+        let active = repo
+            .list(&ImpersonationListQuery {
+                user_id: Some("alice".to_string()),
+                status: Some(ImpersonationStatus::Active),
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+        assert_eq!(active.len(), 1);
+        assert_eq!(active[0].id, forced.id);
+        assert_eq!(
+            repo.list(&ImpersonationListQuery::default())
+                .await
+                .unwrap()
+                .len(),
+            3
+        );
+    }
+}
@@ -0,0 +1,205 @@
+//! CSV import/export of invite codes.
+//!
+//! Exported files use the columns of `EXPORT_COLUMNS`; imports need a header
+//! row with at least a `code` column, so files from other tools (or a plain
+//! one-column list of pre-generated codes) work as long as the header
+//! names match. Unknown columns are ignored.
+
+use anyhow::{Result, bail};
+
+use super::models::{ImportInviteCode, InviteCode};
+
+/// Columns written by `export`, in order.
+pub const EXPORT_COLUMNS: &[&str] = &[
+    "code",
+    "max_uses",
+    "uses_remaining",
+    "expires_at",
+    "note",
+    "created_at",
+    "last_used_at",
+];
+
+/// Render invite codes as CSV with a header row.
+pub fn export(codes: &[InviteCode]) -> String {
+    let mut out = String::new();
+    write_record(&mut out, EXPORT_COLUMNS.iter().copied());
+    for code in codes {
+        let max_uses = code.max_uses.to_string();
+        let uses_remaining = code.uses_remaining.to_string();
+        write_record(
+            &mut out,
+            [
+                code.code.as_str(),
+                &max_uses,
+                &uses_remaining,
+                code.expires_at.as_deref().unwrap_or_default(),
+                code.note.as_deref().unwrap_or_default(),
+                &code.created_at,
+                code.last_used_at.as_deref().unwrap_or_default(),
+            ],
+        );
+    }
+    out
+}
+
+/// Parse invite codes from CSV. Empty cells are treated as missing values.
+pub fn parse(input: &str) -> Result<Vec<ImportInviteCode>> {
+    let mut records = parse_records(input)?.into_iter();
+    let Some(header) = records.next() else {
+        bail!("CSV is empty");
+    };
+    let column = |name: &str| {
+        header
+            .iter()
+            .position(|h| h.trim().eq_ignore_ascii_case(name))
+    };
+    let Some(code_col) = column("code") else {
+        bail!("CSV header has no 'code' column");
+    };
+    let max_uses_col = column("max_uses");
+    let uses_remaining_col = column("uses_remaining");
+    let expires_at_col = column("expires_at");
+    let note_col = column("note");
+
+    let mut codes = Vec::new();
+    for (index, record) in records.enumerate() {
+        // Header is line 1.
+        let line = index + 2;
+        if record.iter().all(|cell| cell.trim().is_empty()) {
+            continue;
+        }
+        let cell = |col: Option<usize>| {
+            col.and_then(|c| record.get(c))
+                .map(|v| v.trim())
+                .filter(|v| !v.is_empty())
+        };
+        let number = |col: Option<usize>, name: &str| -> Result<Option<i32>> {
+            cell(col)
+                .map(|v| {
+                    v.parse::<i32>()
+                        .map_err(|_| anyhow::anyhow!("line {line}: invalid {name} '{v}'"))
+                })
+                .transpose()
+        };
+        codes.push(ImportInviteCode {
+            code: cell(Some(code_col)).unwrap_or_default().to_string(),
+            max_uses: number(max_uses_col, "max_uses")?,
+            uses_remaining: number(uses_remaining_col, "uses_remaining")?,
+            expires_at: cell(expires_at_col).map(ToString::to_string),
+            note: cell(note_col).map(ToString::to_string),
+        });
+    }
+    Ok(codes)
+}
+
+pub(crate) fn write_record<'a>(out: &mut String, fields: impl IntoIterator<Item = &'a str>) {
+    for (i, field) in fields.into_iter().enumerate() {
+        if i > 0 {
+            out.push(',');
+        }
+        if field.contains([',', '"', '\n', '\r']) {
+            out.push('"');
+            out.push_str(&field.replace('"', "\"\""));
+            out.push('"');
+        } else {
+            out.push_str(field);
+        }
+    }
+    out.push('\n');
+}
+
+/// Split CSV text into records (RFC 4180 quoting, `\n` or `\r\n` line ends).
+fn parse_records(input: &str) -> Result<Vec<Vec<String>>> {
+    let input = input.strip_prefix('\u{feff}').unwrap_or(input);
+    let mut records = Vec::new();
+    let mut record = Vec::new();
+    let mut field = String::new();
+    let mut in_quotes = false;
+    let mut chars = input.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        if in_quotes {
+            match c {
+                '"' if chars.peek() == Some(&'"') => {
+                    chars.next();
+                    field.push('"');
+                }
+                '"' => in_quotes = false,
+                _ => field.push(c),
+            }
+            continue;
+        }
+        match c {
+            '"' if field.is_empty() => in_quotes = true,
+            ',' => record.push(std::mem::take(&mut field)),
+            '\r' if chars.peek() == Some(&'\n') => {}
+            '\n' => {
+                record.push(std::mem::take(&mut field));
+                records.push(std::mem::take(&mut record));
+            }
+            _ => field.push(c),
+        }
+    }
+    if in_quotes {
+        bail!("CSV has an unterminated quoted field");
+    }
+    if !field.is_empty() || !record.is_empty() {
+        record.push(field);
+        records.push(record);
+    }
+    Ok(records)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn code(code: &str, note: Option<&str>) -> InviteCode {
+        InviteCode {
+            id: "inv_1".to_string(),
+            code: code.to_string(),
+            created_by: "usr_admin".to_string(),
+            used_by: None,
+            uses_remaining: 2,
+            max_uses: 3,
+            expires_at: Some("2030-01-01 00:00:00".to_string()),
+            created_at: "2026-01-01 00:00:00".to_string(),
+            last_used_at: None,
+            note: note.map(ToString::to_string),
+        }
+    }
+
+    #[test]
+    fn test_export_roundtrip() {
+        let codes = vec![
+            code("SPRING-1", Some("newsletter, \"spring\"")),
+            code("SPRING-2", None),
+        ];
+        let csv = export(&codes);
+        assert!(csv.starts_with("code,max_uses,uses_remaining,"));
+
+        let parsed = parse(&csv).unwrap();
+        assert_eq!(parsed.len(), 2);
+        assert_eq!(parsed[0].code, "SPRING-1");
+        assert_eq!(parsed[0].note.as_deref(), Some("newsletter, \"spring\""));
+        assert_eq!(parsed[0].max_uses, Some(3));
+        assert_eq!(parsed[0].uses_remaining, Some(2));
+        assert_eq!(parsed[0].expires_at.as_deref(), Some("2030-01-01 00:00:00"));
+        assert_eq!(parsed[1].note, None);
+    }
+
+    #[test]
+    fn test_parse_foreign_files() {
+        let parsed = parse("\u{feff}Code;x\r\nABC\r\n\r\nDEF\r\n").unwrap_err();
+        assert!(parsed.to_string().contains("'code'"));
+
+        let parsed = parse("campaign,Code\r\nmail,ABC\r\n,\r\nweb,DEF").unwrap();
+        let codes: Vec<_> = parsed.iter().map(|c| c.code.as_str()).collect();
+        assert_eq!(codes, ["ABC", "DEF"]);
+        assert_eq!(parsed[0].max_uses, None);
+
+        assert!(parse("code,max_uses\nABC,many\n").is_err());
+        assert!(parse("code\n\"ABC\n").is_err());
+    }
+}
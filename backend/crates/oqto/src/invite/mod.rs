@@ -1,14 +1,18 @@
 //! Invite code module for self-service registration.
 //!
 //! Provides invite code generation, validation, and management for
-//! controlling user registration.
+//! controlling user registration, CSV import/export of codes generated
+//! elsewhere, and a per-code redemption history.
 
+pub mod csv;
 mod models;
 mod repository;
 
 #[allow(unused_imports)]
 pub use models::{
-    BatchCreateInviteCodesRequest, CreateInviteCodeRequest, InviteCode, InviteCodeListQuery,
-    InviteCodeSummary,
+    BatchCreateInviteCodesRequest, BatchRevokeInviteCodesRequest, CreateInviteCodeRequest,
+    DuplicateInviteCode, DuplicateReason, ImportInviteCode, ImportInviteCodesRequest,
+    ImportInviteCodesResult, InviteCode, InviteCodeExportQuery, InviteCodeListQuery,
+    InviteCodeRedemption, InviteCodeSummary,
 };
 pub use repository::InviteCodeRepository;
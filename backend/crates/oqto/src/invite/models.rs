@@ -155,3 +155,97 @@ impl From<InviteCode> for InviteCodeSummary {
         }
     }
 }
+
+/// One invite code to import (from JSON or a CSV row).
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct ImportInviteCode {
+    /// The code string, kept as given (trimmed).
+    pub code: String,
+    /// Total uses. Defaults to the request's `max_uses`.
+    #[serde(default)]
+    pub max_uses: Option<i32>,
+    /// Uses left. Defaults to `max_uses`; lower values keep partly used
+    /// codes partly used when re-importing an export.
+    #[serde(default)]
+    pub uses_remaining: Option<i32>,
+    /// Expiry as RFC 3339 or `YYYY-MM-DD HH:MM:SS` (UTC).
+    #[serde(default)]
+    pub expires_at: Option<String>,
+    /// Defaults to the request's `note`.
+    #[serde(default)]
+    pub note: Option<String>,
+}
+
+/// Request to import pre-generated invite codes.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct ImportInviteCodesRequest {
+    #[serde(default)]
+    pub codes: Vec<ImportInviteCode>,
+    /// CSV text with a header row; parsed and appended to `codes`.
+    #[serde(default)]
+    pub csv: Option<String>,
+    /// Uses per code where a row does not say.
+    #[serde(default = "default_uses")]
+    pub max_uses: i32,
+    /// Note for codes without one.
+    #[serde(default)]
+    pub note: Option<String>,
+}
+
+/// Why an imported code was skipped.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DuplicateReason {
+    /// A code with the same string already exists.
+    AlreadyExists,
+    /// The code appeared earlier in the same import.
+    RepeatedInImport,
+}
+
+/// A skipped duplicate of an import.
+#[derive(Debug, Clone, Serialize)]
+pub struct DuplicateInviteCode {
+    pub code: String,
+    pub reason: DuplicateReason,
+}
+
+/// Result of an import.
+#[derive(Debug, Clone, Serialize)]
+pub struct ImportInviteCodesResult {
+    pub imported: Vec<InviteCodeSummary>,
+    pub duplicates: Vec<DuplicateInviteCode>,
+}
+
+/// Request to revoke every code matching a note and/or prefix.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct BatchRevokeInviteCodesRequest {
+    /// Exact note of the codes to revoke.
+    #[serde(default)]
+    pub note: Option<String>,
+    /// Code prefix (case-sensitive), e.g. `SPRING-`.
+    #[serde(default)]
+    pub prefix: Option<String>,
+}
+
+/// Query parameters for exporting invite codes.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct InviteCodeExportQuery {
+    /// `csv` (default) or `json`.
+    pub format: Option<String>,
+    /// Filter by validity (true = still usable, false = exhausted/expired).
+    pub valid: Option<bool>,
+    /// Filter by creator.
+    pub created_by: Option<String>,
+}
+
+/// One redemption of an invite code.
+#[derive(Debug, Clone, Serialize, FromRow)]
+pub struct InviteCodeRedemption {
+    pub id: String,
+    pub invite_code_id: String,
+    /// Redeeming user; None once the user was deleted.
+    pub user_id: Option<String>,
+    /// Username at redemption time.
+    pub username: String,
+    pub redeemed_at: String,
+}
@@ -1,29 +1,36 @@
 //! Invite code repository for database operations.
 
-use anyhow::{Context, Result};
-use sqlx::SqlitePool;
+use std::collections::HashSet;
+
+use anyhow::{Context, Result, bail};
+use sqlx::QueryBuilder;
 use tracing::{debug, instrument};
 
-use super::models::{CreateInviteCodeRequest, InviteCode, InviteCodeListQuery};
+use super::models::{
+    CreateInviteCodeRequest, DuplicateInviteCode, DuplicateReason, ImportInviteCode,
+    ImportInviteCodesRequest, ImportInviteCodesResult, InviteCode, InviteCodeExportQuery,
+    InviteCodeListQuery, InviteCodeRedemption,
+};
+use crate::db::{self, DbPool, on_pool};
+
+/// Most codes one import or export handles.
+const MAX_IMPORT_EXPORT_CODES: usize = 10_000;
+
+/// Longest accepted imported code.
+const MAX_CODE_LEN: usize = 64;
 
 /// Repository for invite code database operations.
 #[derive(Debug, Clone)]
 pub struct InviteCodeRepository {
-    pool: SqlitePool,
+    pool: DbPool,
 }
 
 impl InviteCodeRepository {
     /// Create a new invite code repository.
-    pub fn new(pool: SqlitePool) -> Self {
+    pub fn new(pool: DbPool) -> Self {
         Self { pool }
     }
 
-    /// Get a reference to the database pool.
-    /// Used for operations that need direct database access within transactions.
-    pub fn pool(&self) -> &SqlitePool {
-        &self.pool
-    }
-
     /// Generate a new invite code ID.
     fn generate_id() -> String {
         format!("inv_{}", nanoid::nanoid!(12))
@@ -55,10 +62,10 @@ impl InviteCodeRepository {
 
         debug!("Creating invite code: {} ({})", code, id);
 
-        sqlx::query(
+        on_pool!(&self.pool, |pool| sqlx::query(
             r#"
             INSERT INTO invite_codes (id, code, created_by, uses_remaining, max_uses, expires_at, note)
-            VALUES (?, ?, ?, ?, ?, ?, ?)
+            VALUES ($1, $2, $3, $4, $5, $6, $7)
             "#,
         )
         .bind(&id)
@@ -68,8 +75,9 @@ impl InviteCodeRepository {
         .bind(request.max_uses)
         .bind(&expires_at)
         .bind(&request.note)
-        .execute(&self.pool)
+        .execute(pool)
         .await
+        .map(|r| r.rows_affected()))
         .context("Failed to insert invite code")?;
 
         self.get(&id)
@@ -99,10 +107,10 @@ impl InviteCodeRepository {
             let id = Self::generate_id();
             let code = Self::generate_code(prefix);
 
-            sqlx::query(
+            on_pool!(&self.pool, |pool| sqlx::query(
                 r#"
                 INSERT INTO invite_codes (id, code, created_by, uses_remaining, max_uses, expires_at, note)
-                VALUES (?, ?, ?, ?, ?, ?, ?)
+                VALUES ($1, $2, $3, $4, $5, $6, $7)
                 "#,
             )
             .bind(&id)
@@ -112,8 +120,9 @@ impl InviteCodeRepository {
             .bind(uses_per_code)
             .bind(&expires_at)
             .bind(note)
-            .execute(&self.pool)
+            .execute(pool)
             .await
+            .map(|r| r.rows_affected()))
             .context("Failed to insert invite code")?;
 
             if let Some(invite_code) = self.get(&id).await? {
@@ -128,17 +137,17 @@ impl InviteCodeRepository {
     /// Get an invite code by ID.
     #[instrument(skip(self))]
     pub async fn get(&self, id: &str) -> Result<Option<InviteCode>> {
-        let code = sqlx::query_as::<_, InviteCode>(
+        let code = on_pool!(&self.pool, |pool| sqlx::query_as::<_, InviteCode>(
             r#"
             SELECT id, code, created_by, used_by, uses_remaining, max_uses,
                    expires_at, created_at, last_used_at, note
             FROM invite_codes
-            WHERE id = ?
+            WHERE id = $1
             "#,
         )
         .bind(id)
-        .fetch_optional(&self.pool)
-        .await
+        .fetch_optional(pool)
+        .await)
         .context("Failed to fetch invite code")?;
 
         Ok(code)
@@ -147,17 +156,17 @@ impl InviteCodeRepository {
     /// Get an invite code by the code string.
     #[instrument(skip(self))]
     pub async fn get_by_code(&self, code: &str) -> Result<Option<InviteCode>> {
-        let invite = sqlx::query_as::<_, InviteCode>(
+        let invite = on_pool!(&self.pool, |pool| sqlx::query_as::<_, InviteCode>(
             r#"
             SELECT id, code, created_by, used_by, uses_remaining, max_uses,
                    expires_at, created_at, last_used_at, note
             FROM invite_codes
-            WHERE code = ?
+            WHERE code = $1
             "#,
         )
         .bind(code)
-        .fetch_optional(&self.pool)
-        .await
+        .fetch_optional(pool)
+        .await)
         .context("Failed to fetch invite code by code")?;
 
         Ok(invite)
@@ -169,46 +178,31 @@ impl InviteCodeRepository {
         let limit = query.limit.unwrap_or(100);
         let offset = query.offset.unwrap_or(0);
 
-        let mut sql = String::from(
-            r#"
-            SELECT id, code, created_by, used_by, uses_remaining, max_uses,
-                   expires_at, created_at, last_used_at, note
-            FROM invite_codes
-            WHERE 1=1
-            "#,
-        );
-
-        let mut bind_values: Vec<String> = Vec::new();
-
-        if let Some(created_by) = &query.created_by {
-            sql.push_str(" AND created_by = ?");
-            bind_values.push(created_by.clone());
-        }
+        let codes = on_pool!(&self.pool, |pool| {
+            let mut qb = db::query_builder(
+                pool,
+                r#"
+                SELECT id, code, created_by, used_by, uses_remaining, max_uses,
+                       expires_at, created_at, last_used_at, note
+                FROM invite_codes
+                WHERE 1=1
+                "#,
+            );
 
-        if let Some(valid) = query.valid {
-            if valid {
-                // Valid = has uses remaining AND (no expiry OR expiry in future)
-                sql.push_str(" AND uses_remaining > 0 AND (expires_at IS NULL OR expires_at > datetime('now'))");
-            } else {
-                // Invalid = exhausted OR expired
-                sql.push_str(" AND (uses_remaining <= 0 OR (expires_at IS NOT NULL AND expires_at <= datetime('now')))");
+            if let Some(created_by) = &query.created_by {
+                qb.push(" AND created_by = ").push_bind(created_by.clone());
             }
-        }
-
-        sql.push_str(" ORDER BY created_at DESC LIMIT ? OFFSET ?");
-
-        let mut query_builder = sqlx::query_as::<_, InviteCode>(&sql);
 
-        for value in &bind_values {
-            query_builder = query_builder.bind(value);
-        }
+            push_validity_filter(&mut qb, query.valid);
 
-        query_builder = query_builder.bind(limit).bind(offset);
+            qb.push(" ORDER BY created_at DESC LIMIT ")
+                .push_bind(limit)
+                .push(" OFFSET ")
+                .push_bind(offset);
 
-        let codes = query_builder
-            .fetch_all(&self.pool)
-            .await
-            .context("Failed to list invite codes")?;
+            qb.build_query_as::<InviteCode>().fetch_all(pool).await
+        })
+        .context("Failed to list invite codes")?;
 
         Ok(codes)
     }
@@ -238,19 +232,21 @@ impl InviteCodeRepository {
         }
 
         // Decrement uses and record user
-        sqlx::query(
+        on_pool!(&self.pool, |pool| sqlx::query(
             r#"
             UPDATE invite_codes
             SET uses_remaining = uses_remaining - 1,
-                used_by = ?,
-                last_used_at = datetime('now')
-            WHERE id = ? AND uses_remaining > 0
+                used_by = $1,
+                last_used_at = $2
+            WHERE id = $3 AND uses_remaining > 0
             "#,
         )
         .bind(user_id)
+        .bind(db::now())
         .bind(&invite.id)
-        .execute(&self.pool)
+        .execute(pool)
         .await
+        .map(|r| r.rows_affected()))
         .context("Failed to consume invite code")?;
 
         self.get(&invite.id)
@@ -277,22 +273,24 @@ impl InviteCodeRepository {
         // NOTE: We do NOT set used_by here because the user hasn't been
         // created yet — the column has a FOREIGN KEY constraint on users(id).
         // used_by is set later via update_used_by() after user creation.
-        let result = sqlx::query(
+        let result = on_pool!(&self.pool, |pool| sqlx::query(
             r#"
             UPDATE invite_codes
             SET uses_remaining = uses_remaining - 1,
-                last_used_at = datetime('now')
-            WHERE code = ?
+                last_used_at = $1
+            WHERE code = $2
               AND uses_remaining > 0
-              AND (expires_at IS NULL OR expires_at > datetime('now'))
+              AND (expires_at IS NULL OR expires_at > $1)
             "#,
         )
+        .bind(db::now())
         .bind(code)
-        .execute(&self.pool)
+        .execute(pool)
         .await
+        .map(|r| r.rows_affected()))
         .context("Failed to consume invite code")?;
 
-        if result.rows_affected() == 0 {
+        if result == 0 {
             // No rows affected means either:
             // 1. Code doesn't exist
             // 2. Code is exhausted (uses_remaining <= 0)
@@ -333,19 +331,20 @@ impl InviteCodeRepository {
     /// but the subsequent operation (like user creation) failed.
     #[instrument(skip(self))]
     pub async fn restore_use(&self, code: &str) -> Result<()> {
-        let result = sqlx::query(
+        let result = on_pool!(&self.pool, |pool| sqlx::query(
             r#"
             UPDATE invite_codes
             SET uses_remaining = uses_remaining + 1
-            WHERE code = ? AND uses_remaining < max_uses
+            WHERE code = $1 AND uses_remaining < max_uses
             "#,
         )
         .bind(code)
-        .execute(&self.pool)
+        .execute(pool)
         .await
+        .map(|r| r.rows_affected()))
         .context("Failed to restore invite code use")?;
 
-        if result.rows_affected() == 0 {
+        if result == 0 {
             // Code doesn't exist or already at max uses - log but don't fail
             debug!("restore_use: no rows affected for code {}", code);
         }
@@ -356,19 +355,20 @@ impl InviteCodeRepository {
     /// Revoke an invite code (set uses_remaining to 0).
     #[instrument(skip(self))]
     pub async fn revoke(&self, id: &str) -> Result<()> {
-        let result = sqlx::query(
+        let result = on_pool!(&self.pool, |pool| sqlx::query(
             r#"
             UPDATE invite_codes
             SET uses_remaining = 0
-            WHERE id = ?
+            WHERE id = $1
             "#,
         )
         .bind(id)
-        .execute(&self.pool)
+        .execute(pool)
         .await
+        .map(|r| r.rows_affected()))
         .context("Failed to revoke invite code")?;
 
-        if result.rows_affected() == 0 {
+        if result == 0 {
             return Err(anyhow::anyhow!("Invite code not found: {}", id));
         }
 
@@ -378,13 +378,16 @@ impl InviteCodeRepository {
     /// Delete an invite code.
     #[instrument(skip(self))]
     pub async fn delete(&self, id: &str) -> Result<()> {
-        let result = sqlx::query("DELETE FROM invite_codes WHERE id = ?")
-            .bind(id)
-            .execute(&self.pool)
-            .await
-            .context("Failed to delete invite code")?;
+        let result = on_pool!(&self.pool, |pool| sqlx::query(
+            "DELETE FROM invite_codes WHERE id = $1"
+        )
+        .bind(id)
+        .execute(pool)
+        .await
+        .map(|r| r.rows_affected()))
+        .context("Failed to delete invite code")?;
 
-        if result.rows_affected() == 0 {
+        if result == 0 {
             return Err(anyhow::anyhow!("Invite code not found: {}", id));
         }
 
@@ -394,10 +397,12 @@ impl InviteCodeRepository {
     /// Count total invite codes.
     #[instrument(skip(self))]
     pub async fn count(&self) -> Result<i64> {
-        let count: (i64,) = sqlx::query_as("SELECT COUNT(*) FROM invite_codes")
-            .fetch_one(&self.pool)
-            .await
-            .context("Failed to count invite codes")?;
+        let count: (i64,) = on_pool!(&self.pool, |pool| sqlx::query_as(
+            "SELECT COUNT(*) FROM invite_codes"
+        )
+        .fetch_one(pool)
+        .await)
+        .context("Failed to count invite codes")?;
 
         Ok(count.0)
     }
@@ -405,19 +410,300 @@ impl InviteCodeRepository {
     /// Count valid (still usable) invite codes.
     #[instrument(skip(self))]
     pub async fn count_valid(&self) -> Result<i64> {
-        let count: (i64,) = sqlx::query_as(
+        let count: (i64,) = on_pool!(&self.pool, |pool| sqlx::query_as(
             r#"
             SELECT COUNT(*) FROM invite_codes
             WHERE uses_remaining > 0
-            AND (expires_at IS NULL OR expires_at > datetime('now'))
+            AND (expires_at IS NULL OR expires_at > $1)
             "#,
         )
-        .fetch_one(&self.pool)
-        .await
+        .bind(db::now())
+        .fetch_one(pool)
+        .await)
         .context("Failed to count valid invite codes")?;
 
         Ok(count.0)
     }
+
+    /// List every invite code matching the export filters, oldest first.
+    #[instrument(skip(self))]
+    pub async fn list_for_export(&self, query: &InviteCodeExportQuery) -> Result<Vec<InviteCode>> {
+        on_pool!(&self.pool, |pool| {
+            let mut qb = db::query_builder(
+                pool,
+                r#"
+                SELECT id, code, created_by, used_by, uses_remaining, max_uses,
+                       expires_at, created_at, last_used_at, note
+                FROM invite_codes
+                WHERE 1=1
+                "#,
+            );
+            if let Some(created_by) = &query.created_by {
+                qb.push(" AND created_by = ").push_bind(created_by.clone());
+            }
+            push_validity_filter(&mut qb, query.valid);
+            qb.push(" ORDER BY created_at, code LIMIT ")
+                .push_bind(MAX_IMPORT_EXPORT_CODES as i64);
+
+            qb.build_query_as::<InviteCode>().fetch_all(pool).await
+        })
+        .context("Failed to export invite codes")
+    }
+
+    /// Import pre-generated codes in one transaction.
+    ///
+    /// Codes that already exist, or repeat earlier in the import, are skipped
+    /// and reported. Any invalid row fails the whole import before anything
+    /// is written.
+    #[instrument(skip(self, request, created_by), fields(count = request.codes.len()))]
+    pub async fn import(
+        &self,
+        request: ImportInviteCodesRequest,
+        created_by: &str,
+    ) -> Result<ImportInviteCodesResult> {
+        let mut rows = request.codes;
+        if let Some(csv) = request.csv.as_deref() {
+            rows.extend(super::csv::parse(csv)?);
+        }
+        if rows.is_empty() {
+            bail!("no invite codes to import");
+        }
+        if rows.len() > MAX_IMPORT_EXPORT_CODES {
+            bail!("at most {MAX_IMPORT_EXPORT_CODES} invite codes per import");
+        }
+
+        let mut prepared = Vec::with_capacity(rows.len());
+        let mut duplicates = Vec::new();
+        let mut seen = HashSet::new();
+        for (index, row) in rows.into_iter().enumerate() {
+            let row = prepare_import(row, request.max_uses, request.note.as_deref())
+                .with_context(|| format!("code {}", index + 1))?;
+            if seen.insert(row.code.clone()) {
+                prepared.push(row);
+            } else {
+                duplicates.push(DuplicateInviteCode {
+                    code: row.code,
+                    reason: DuplicateReason::RepeatedInImport,
+                });
+            }
+        }
+
+        let mut imported_ids = Vec::with_capacity(prepared.len());
+        on_pool!(&self.pool, |pool| {
+            let mut tx = pool.begin().await.context("Failed to begin import")?;
+            for row in prepared {
+                let id = Self::generate_id();
+                let result = sqlx::query(
+                    r#"
+                    INSERT INTO invite_codes (id, code, created_by, uses_remaining, max_uses, expires_at, note)
+                    VALUES ($1, $2, $3, $4, $5, $6, $7)
+                    ON CONFLICT(code) DO NOTHING
+                    "#,
+                )
+                .bind(&id)
+                .bind(&row.code)
+                .bind(created_by)
+                .bind(row.uses_remaining.unwrap_or_default())
+                .bind(row.max_uses.unwrap_or_default())
+                .bind(&row.expires_at)
+                .bind(&row.note)
+                .execute(&mut *tx)
+                .await
+                .context("Failed to import invite code")?;
+                if result.rows_affected() == 0 {
+                    duplicates.push(DuplicateInviteCode {
+                        code: row.code,
+                        reason: DuplicateReason::AlreadyExists,
+                    });
+                } else {
+                    imported_ids.push(id);
+                }
+            }
+            tx.commit().await.context("Failed to commit import")?;
+        });
+
+        let mut imported = Vec::with_capacity(imported_ids.len());
+        for id in &imported_ids {
+            if let Some(code) = self.get(id).await? {
+                imported.push(code.into());
+            }
+        }
+        debug!(
+            imported = imported.len(),
+            duplicates = duplicates.len(),
+            "Imported invite codes"
+        );
+        Ok(ImportInviteCodesResult {
+            imported,
+            duplicates,
+        })
+    }
+
+    /// Revoke every still-usable code with the given note and/or prefix.
+    /// Returns how many codes were revoked.
+    #[instrument(skip(self))]
+    pub async fn revoke_matching(&self, note: Option<&str>, prefix: Option<&str>) -> Result<u64> {
+        let prefix = prefix.filter(|p| !p.is_empty());
+        if note.is_none() && prefix.is_none() {
+            bail!("a note or prefix is required");
+        }
+        on_pool!(&self.pool, |pool| {
+            let mut qb = db::query_builder(
+                pool,
+                "UPDATE invite_codes SET uses_remaining = 0 WHERE uses_remaining > 0",
+            );
+            if let Some(note) = note {
+                qb.push(" AND note = ").push_bind(note.to_string());
+            }
+            if let Some(prefix) = prefix {
+                // substr instead of LIKE so `%` and `_` in prefixes match literally.
+                qb.push(" AND substr(code, 1, ")
+                    .push_bind(prefix.chars().count() as i32)
+                    .push(") = ")
+                    .push_bind(prefix.to_string());
+            }
+            qb.build().execute(pool).await.map(|r| r.rows_affected())
+        })
+        .context("Failed to revoke invite codes")
+    }
+
+    /// Record a successful registration with a code: who redeemed it, when.
+    #[instrument(skip(self))]
+    pub async fn record_redemption(&self, code: &str, user_id: &str, username: &str) -> Result<()> {
+        on_pool!(&self.pool, |pool| {
+            let mut tx = pool.begin().await.context("Failed to begin redemption")?;
+            let invite_id: Option<String> = sqlx::query_scalar(
+                "UPDATE invite_codes SET used_by = $1 WHERE code = $2 RETURNING id",
+            )
+            .bind(user_id)
+            .bind(code)
+            .fetch_optional(&mut *tx)
+            .await
+            .context("Failed to update invite code used_by")?;
+            let Some(invite_id) = invite_id else {
+                bail!("Invite code not found");
+            };
+            sqlx::query(
+                r#"
+                INSERT INTO invite_code_redemptions (id, invite_code_id, user_id, username)
+                VALUES ($1, $2, $3, $4)
+                "#,
+            )
+            .bind(format!("red_{}", nanoid::nanoid!(12)))
+            .bind(&invite_id)
+            .bind(user_id)
+            .bind(username)
+            .execute(&mut *tx)
+            .await
+            .context("Failed to record invite code redemption")?;
+            tx.commit().await.context("Failed to commit redemption")?;
+        });
+        Ok(())
+    }
+
+    /// Usage history of a code, oldest first.
+    #[instrument(skip(self))]
+    pub async fn list_redemptions(
+        &self,
+        invite_code_id: &str,
+    ) -> Result<Vec<InviteCodeRedemption>> {
+        // Postgres has no rowid; its table carries an insertion sequence instead.
+        let sql = if self.pool.is_postgres() {
+            REDEMPTIONS_SQL.replace("rowid", "seq")
+        } else {
+            REDEMPTIONS_SQL.to_string()
+        };
+        on_pool!(&self.pool, |pool| {
+            sqlx::query_as::<_, InviteCodeRedemption>(&sql)
+                .bind(invite_code_id)
+                .fetch_all(pool)
+                .await
+        })
+        .context("Failed to list invite code redemptions")
+    }
+}
+
+/// Usage history query, ordered by insertion within the same second.
+const REDEMPTIONS_SQL: &str = r#"
+    SELECT id, invite_code_id, user_id, username, redeemed_at
+    FROM invite_code_redemptions
+    WHERE invite_code_id = $1
+    ORDER BY redeemed_at, rowid
+"#;
+
+/// Restrict a query to valid (`Some(true)`) or invalid (`Some(false)`) codes.
+fn push_validity_filter<'a, DB: sqlx::Database>(qb: &mut QueryBuilder<'a, DB>, valid: Option<bool>)
+where
+    String: sqlx::Encode<'a, DB> + sqlx::Type<DB>,
+{
+    match valid {
+        Some(true) => {
+            // Valid = has uses remaining AND (no expiry OR expiry in future)
+            qb.push(" AND uses_remaining > 0 AND (expires_at IS NULL OR expires_at > ")
+                .push_bind(db::now())
+                .push(")");
+        }
+        Some(false) => {
+            // Invalid = exhausted OR expired
+            qb.push(" AND (uses_remaining <= 0 OR (expires_at IS NOT NULL AND expires_at <= ")
+                .push_bind(db::now())
+                .push("))");
+        }
+        None => {}
+    }
+}
+
+/// Validate an imported row and fill in defaults.
+fn prepare_import(
+    mut row: ImportInviteCode,
+    default_max_uses: i32,
+    default_note: Option<&str>,
+) -> Result<ImportInviteCode> {
+    let code = row.code.trim();
+    if code.is_empty() {
+        bail!("code is empty");
+    }
+    if code.chars().count() > MAX_CODE_LEN {
+        bail!("code '{code}' is longer than {MAX_CODE_LEN} characters");
+    }
+    if code.chars().any(|c| c.is_whitespace() || c.is_control()) {
+        bail!("code '{code}' contains whitespace");
+    }
+    row.code = code.to_string();
+
+    let max_uses = row.max_uses.unwrap_or(default_max_uses);
+    if max_uses < 1 {
+        bail!("max_uses of '{code}' must be at least 1");
+    }
+    let uses_remaining = row.uses_remaining.unwrap_or(max_uses);
+    if !(0..=max_uses).contains(&uses_remaining) {
+        bail!("uses_remaining of '{code}' must be between 0 and max_uses");
+    }
+    row.max_uses = Some(max_uses);
+    row.uses_remaining = Some(uses_remaining);
+
+    row.expires_at = row
+        .expires_at
+        .as_deref()
+        .map(|value| {
+            normalize_expiry(value)
+                .ok_or_else(|| anyhow::anyhow!("invalid expires_at '{value}' of '{code}'"))
+        })
+        .transpose()?;
+    row.note = row.note.or_else(|| default_note.map(ToString::to_string));
+    Ok(row)
+}
+
+/// Convert an RFC 3339 or SQLite timestamp to the stored SQLite format.
+fn normalize_expiry(value: &str) -> Option<String> {
+    let value = value.trim();
+    let parsed = chrono::DateTime::parse_from_rfc3339(value)
+        .map(|t| t.with_timezone(&chrono::Utc))
+        .or_else(|_| {
+            chrono::NaiveDateTime::parse_from_str(value, "%Y-%m-%d %H:%M:%S").map(|t| t.and_utc())
+        })
+        .ok()?;
+    Some(parsed.format("%Y-%m-%d %H:%M:%S").to_string())
 }
 
 #[cfg(test)]
@@ -440,8 +726,8 @@ mod tests {
         }
     }
 
-    async fn setup_test_db() -> SqlitePool {
-        let pool = SqlitePool::connect(":memory:").await.t();
+    async fn setup_test_db() -> sqlx::SqlitePool {
+        let pool = sqlx::SqlitePool::connect(":memory:").await.t();
 
         // Create users table first (for foreign key)
         sqlx::query(
@@ -495,13 +781,28 @@ mod tests {
         .await
         .t();
 
+        sqlx::query(
+            r#"
+            CREATE TABLE invite_code_redemptions (
+                id TEXT PRIMARY KEY,
+                invite_code_id TEXT NOT NULL REFERENCES invite_codes(id) ON DELETE CASCADE,
+                user_id TEXT REFERENCES users(id) ON DELETE SET NULL,
+                username TEXT NOT NULL,
+                redeemed_at TEXT NOT NULL DEFAULT (datetime('now'))
+            )
+            "#,
+        )
+        .execute(&pool)
+        .await
+        .t();
+
         pool
     }
 
     #[tokio::test]
     async fn test_create_and_get_invite_code() {
         let pool = setup_test_db().await;
-        let repo = InviteCodeRepository::new(pool);
+        let repo = InviteCodeRepository::new(DbPool::Sqlite(pool));
 
         let request = CreateInviteCodeRequest {
             code: Some("TEST123".to_string()),
@@ -528,7 +829,7 @@ mod tests {
     #[tokio::test]
     async fn test_consume_invite_code() {
         let pool = setup_test_db().await;
-        let repo = InviteCodeRepository::new(pool.clone());
+        let repo = InviteCodeRepository::new(DbPool::Sqlite(pool.clone()));
 
         // Create a test user to consume the code
         sqlx::query(
@@ -569,7 +870,7 @@ mod tests {
     #[tokio::test]
     async fn test_batch_create() {
         let pool = setup_test_db().await;
-        let repo = InviteCodeRepository::new(pool);
+        let repo = InviteCodeRepository::new(DbPool::Sqlite(pool));
 
         let codes = repo
             .create_batch(5, 1, None, Some("BATCH"), Some("Batch test"), "usr_admin")
@@ -586,7 +887,7 @@ mod tests {
     #[tokio::test]
     async fn test_revoke_invite_code() {
         let pool = setup_test_db().await;
-        let repo = InviteCodeRepository::new(pool);
+        let repo = InviteCodeRepository::new(DbPool::Sqlite(pool));
 
         let request = CreateInviteCodeRequest {
             code: Some("REVOKE1".to_string()),
@@ -608,7 +909,7 @@ mod tests {
     #[tokio::test]
     async fn test_validate_code() {
         let pool = setup_test_db().await;
-        let repo = InviteCodeRepository::new(pool);
+        let repo = InviteCodeRepository::new(DbPool::Sqlite(pool));
 
         let request = CreateInviteCodeRequest {
             code: Some("VALID1".to_string()),
@@ -629,7 +930,7 @@ mod tests {
     #[tokio::test]
     async fn test_try_consume_atomic() {
         let pool = setup_test_db().await;
-        let repo = InviteCodeRepository::new(pool.clone());
+        let repo = InviteCodeRepository::new(DbPool::Sqlite(pool.clone()));
 
         // Create a test user to consume the code
         sqlx::query(
@@ -667,7 +968,7 @@ mod tests {
     #[tokio::test]
     async fn test_try_consume_atomic_nonexistent() {
         let pool = setup_test_db().await;
-        let repo = InviteCodeRepository::new(pool);
+        let repo = InviteCodeRepository::new(DbPool::Sqlite(pool));
 
         // Try to consume a code that doesn't exist
         let result = repo.try_consume_atomic("NONEXISTENT", "usr_test").await;
@@ -681,7 +982,7 @@ mod tests {
     #[tokio::test]
     async fn test_try_consume_atomic_multi_use() {
         let pool = setup_test_db().await;
-        let repo = InviteCodeRepository::new(pool.clone());
+        let repo = InviteCodeRepository::new(DbPool::Sqlite(pool.clone()));
 
         // Create test users
         sqlx::query(
@@ -719,7 +1020,7 @@ mod tests {
     #[tokio::test]
     async fn test_restore_use() {
         let pool = setup_test_db().await;
-        let repo = InviteCodeRepository::new(pool.clone());
+        let repo = InviteCodeRepository::new(DbPool::Sqlite(pool.clone()));
 
         // Create a test user
         sqlx::query(
@@ -773,7 +1074,7 @@ mod tests {
         use tokio::sync::Barrier;
 
         let pool = setup_test_db().await;
-        let repo = Arc::new(InviteCodeRepository::new(pool.clone()));
+        let repo = Arc::new(InviteCodeRepository::new(DbPool::Sqlite(pool.clone())));
 
         // Create test users
         for i in 1..=10 {
@@ -841,4 +1142,149 @@ mod tests {
         let invite = repo.get_by_code("CONCURRENT1").await.t().t();
         assert_eq!(invite.uses_remaining, 0);
     }
+
+    #[tokio::test]
+    async fn test_import_skips_duplicates() {
+        let pool = setup_test_db().await;
+        let repo = InviteCodeRepository::new(DbPool::Sqlite(pool));
+        repo.create(
+            CreateInviteCodeRequest {
+                code: Some("EXISTING".to_string()),
+                max_uses: 1,
+                expires_in_secs: None,
+                note: None,
+            },
+            "usr_admin",
+        )
+        .await
+        .t();
+
+        let result = repo
+            .import(
+                ImportInviteCodesRequest {
+                    codes: vec![ImportInviteCode {
+                        code: " EXISTING ".to_string(),
+                        ..Default::default()
+                    }],
+                    csv: Some(
+                        "code,max_uses,expires_at\nNEW-1,3,2030-01-01T00:00:00Z\nNEW-1,1,\nNEW-2,,\n"
+                            .to_string(),
+                    ),
+                    max_uses: 2,
+                    note: Some("partner".to_string()),
+                },
+                "usr_admin",
+            )
+            .await
+            .t();
+
+        let imported: Vec<_> = result.imported.iter().map(|c| c.code.as_str()).collect();
+        assert_eq!(imported, ["NEW-1", "NEW-2"]);
+        assert_eq!(result.imported[0].max_uses, 3);
+        assert_eq!(
+            result.imported[0].expires_at.as_deref(),
+            Some("2030-01-01 00:00:00")
+        );
+        assert_eq!(result.imported[1].max_uses, 2);
+        assert_eq!(result.imported[1].note.as_deref(), Some("partner"));
+        let duplicates: Vec<_> = result
+            .duplicates
+            .iter()
+            .map(|d| (d.code.as_str(), d.reason))
+            .collect();
+        assert_eq!(
+            duplicates,
+            [
+                ("NEW-1", DuplicateReason::RepeatedInImport),
+                ("EXISTING", DuplicateReason::AlreadyExists),
+            ]
+        );
+
+        // Invalid rows fail the import without writing anything.
+        let invalid = repo
+            .import(
+                ImportInviteCodesRequest {
+                    csv: Some("code,max_uses\nGOOD,1\nBAD,0\n".to_string()),
+                    max_uses: 1,
+                    ..Default::default()
+                },
+                "usr_admin",
+            )
+            .await;
+        assert!(invalid.is_err());
+        assert!(repo.get_by_code("GOOD").await.t().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_revoke_matching_by_note_and_prefix() {
+        let pool = setup_test_db().await;
+        let repo = InviteCodeRepository::new(DbPool::Sqlite(pool));
+        repo.create_batch(2, 1, None, Some("100%"), Some("spring"), "usr_admin")
+            .await
+            .t();
+        repo.create_batch(1, 1, None, Some("1000"), Some("spring"), "usr_admin")
+            .await
+            .t();
+        repo.create_batch(1, 1, None, Some("100%"), Some("autumn"), "usr_admin")
+            .await
+            .t();
+
+        assert!(repo.revoke_matching(None, Some("")).await.is_err());
+        assert_eq!(
+            repo.revoke_matching(Some("spring"), Some("100%-"))
+                .await
+                .t(),
+            2
+        );
+        assert_eq!(repo.revoke_matching(Some("spring"), None).await.t(), 1);
+        // Already revoked codes are not counted again.
+        assert_eq!(repo.revoke_matching(None, Some("100%")).await.t(), 1);
+        assert_eq!(repo.count_valid().await.t(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_record_redemption_history() {
+        let pool = setup_test_db().await;
+        let repo = InviteCodeRepository::new(DbPool::Sqlite(pool.clone()));
+        sqlx::query(
+            r#"
+            INSERT INTO users (id, username, email, display_name, role)
+            VALUES ('usr_test', 'testuser', 'test@test.com', 'Test User', 'user')
+            "#,
+        )
+        .execute(&pool)
+        .await
+        .t();
+        let code = repo
+            .create(
+                CreateInviteCodeRequest {
+                    code: Some("HISTORY1".to_string()),
+                    max_uses: 2,
+                    expires_in_secs: None,
+                    note: None,
+                },
+                "usr_admin",
+            )
+            .await
+            .t();
+
+        repo.try_consume_atomic("HISTORY1", "usr_test").await.t();
+        repo.record_redemption("HISTORY1", "usr_test", "testuser")
+            .await
+            .t();
+        assert!(
+            repo.record_redemption("MISSING", "usr_test", "testuser")
+                .await
+                .is_err()
+        );
+
+        let history = repo.list_redemptions(&code.id).await.t();
+        assert_eq!(history.len(), 1);
+        assert_eq!(history[0].user_id.as_deref(), Some("usr_test"));
+        assert_eq!(history[0].username, "testuser");
+        assert_eq!(
+            repo.get(&code.id).await.t().t().used_by.as_deref(),
+            Some("usr_test")
+        );
+    }
 }
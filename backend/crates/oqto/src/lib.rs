@@ -2,38 +2,76 @@
 //!
 //! This library provides the core components for the AI Agent Workspace Platform backend.
 
+pub mod admin_overview;
 pub mod agent_browser;
 pub mod api;
 pub mod api_keys;
 pub mod audit;
 pub mod auth;
+pub mod bookmarks;
 pub mod bus;
 pub mod canon;
+pub mod capabilities;
+pub mod config_reload;
 pub mod container;
+pub mod crash_bundles;
 pub mod db;
 pub mod eavs;
 pub mod feedback;
+pub mod git_credentials;
 pub mod history;
 pub mod hstry;
 pub mod identity;
+pub mod impersonation;
 pub mod invite;
 pub mod local;
+pub mod macros;
 pub mod markdown;
+pub mod mdns;
+pub mod memory_promotion;
+pub mod message_annotations;
+pub mod net;
 pub mod observability;
 pub mod onboarding;
 pub mod oqto_log;
+pub mod outbox;
 pub mod pi;
+pub mod priority_lanes;
+pub mod private_messages;
+pub mod project_cards;
 pub mod projects;
+pub mod prompt_drafts;
 pub mod prompts;
+pub mod redaction;
+pub mod registration;
+pub mod remote_config;
 pub mod runner;
+pub mod scheduler;
+pub mod secrets;
 pub mod session;
+pub mod session_drafts;
+pub mod session_events;
+pub mod session_shares;
+pub mod session_tags;
 pub mod session_target;
 pub mod session_ui;
 pub mod settings;
 pub mod shared_workspace;
+pub mod siem;
+pub mod status;
+pub mod storage;
+pub mod template_tests;
 pub mod templates;
+pub mod tool_usage;
+pub mod triggers;
 pub mod user;
+pub mod user_env;
 pub mod user_plane;
+pub mod voice_health;
+pub mod vuln_scan;
+pub mod webhooks;
 pub mod wordlist;
 pub mod workspace;
+pub mod workspace_access;
+pub mod workspace_backups;
 pub mod ws;
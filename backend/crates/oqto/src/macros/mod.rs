@@ -0,0 +1,249 @@
+//! User-defined macros: named sequences of canonical commands.
+//!
+//! A macro is a list of steps such as "send this prompt", "approve `bash`
+//! permission requests" or "run the `review` template", stored per user. A
+//! run executes the steps in order against one session, substituting
+//! `{{param}}` placeholders with the values passed to the run, and reports
+//! the outcome of every step. The first failing step stops the run.
+
+mod models;
+mod repository;
+mod runner;
+
+use std::collections::{HashMap, HashSet};
+use std::time::Duration;
+
+pub use models::{
+    MacroParam, MacroRun, MacroStep, MacroStepResult, MacrosConfig, RunMacroRequest,
+    SaveMacroRequest, StepStatus, UserMacro,
+};
+pub use repository::MacroRepository;
+pub use runner::{MacroAgent, RunnerAgent, run_steps};
+
+/// Longest accepted macro name.
+pub const MAX_NAME_LEN: usize = 100;
+
+/// Macro storage with the configured limits.
+pub struct MacroService {
+    repo: MacroRepository,
+    config: MacrosConfig,
+}
+
+impl MacroService {
+    pub fn new(repo: MacroRepository, config: MacrosConfig) -> Self {
+        Self { repo, config }
+    }
+
+    pub fn repo(&self) -> &MacroRepository {
+        &self.repo
+    }
+
+    pub fn config(&self) -> &MacrosConfig {
+        &self.config
+    }
+
+    /// How long one step may wait for the agent.
+    pub fn step_timeout(&self) -> Duration {
+        Duration::from_secs(self.config.step_timeout_secs.max(1))
+    }
+}
+
+/// Check a macro definition against the limits. Returns a message for the
+/// caller on failure.
+pub fn validate(request: &SaveMacroRequest, config: &MacrosConfig) -> Result<(), String> {
+    let name = request.name.trim();
+    if name.is_empty() || name.len() > MAX_NAME_LEN {
+        return Err(format!("name must be 1 to {MAX_NAME_LEN} characters"));
+    }
+    if request.steps.is_empty() || request.steps.len() > config.max_steps {
+        return Err(format!("a macro must have 1 to {} steps", config.max_steps));
+    }
+
+    let mut declared = HashSet::new();
+    for param in &request.params {
+        if !is_identifier(&param.name) {
+            return Err(format!(
+                "parameter name '{}' must be letters, digits and underscores",
+                param.name
+            ));
+        }
+        if !declared.insert(param.name.as_str()) {
+            return Err(format!("parameter '{}' is declared twice", param.name));
+        }
+    }
+
+    for (index, step) in request.steps.iter().enumerate() {
+        for text in step.texts() {
+            for placeholder in placeholders(text) {
+                if !declared.contains(placeholder) {
+                    return Err(format!(
+                        "step {index} uses undeclared parameter '{placeholder}'"
+                    ));
+                }
+            }
+        }
+        match step {
+            MacroStep::Template { name, .. } if name.trim().is_empty() => {
+                return Err(format!("step {index}: template name is required"));
+            }
+            MacroStep::Approve { tool_class } if tool_class.trim().is_empty() => {
+                return Err(format!("step {index}: tool_class is required"));
+            }
+            MacroStep::SetModel { provider, model_id }
+                if provider.trim().is_empty() || model_id.trim().is_empty() =>
+            {
+                return Err(format!("step {index}: provider and model_id are required"));
+            }
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+/// The macro's steps with parameters substituted. `provided` overrides the
+/// declared defaults; unknown or missing parameters are an error.
+pub fn resolve_steps(
+    user_macro: &UserMacro,
+    provided: &HashMap<String, String>,
+) -> Result<Vec<MacroStep>, String> {
+    if let Some(unknown) = provided
+        .keys()
+        .find(|key| !user_macro.params.iter().any(|p| &p.name == *key))
+    {
+        return Err(format!("unknown parameter '{unknown}'"));
+    }
+
+    let mut values = HashMap::new();
+    for param in &user_macro.params {
+        let value = provided
+            .get(&param.name)
+            .or(param.default.as_ref())
+            .ok_or_else(|| format!("missing parameter '{}'", param.name))?;
+        values.insert(param.name.as_str(), value.as_str());
+    }
+
+    user_macro
+        .steps
+        .iter()
+        .map(|step| step.map_texts(|text| substitute(text, &values)))
+        .collect()
+}
+
+/// Replace `{{name}}` placeholders. Text outside placeholders is kept as is.
+fn substitute(text: &str, values: &HashMap<&str, &str>) -> Result<String, String> {
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find("{{") {
+        let Some(len) = rest[start + 2..].find("}}") else {
+            break;
+        };
+        let name = rest[start + 2..start + 2 + len].trim();
+        let value = values
+            .get(name)
+            .ok_or_else(|| format!("undeclared parameter '{name}'"))?;
+        out.push_str(&rest[..start]);
+        out.push_str(value);
+        rest = &rest[start + 4 + len..];
+    }
+    out.push_str(rest);
+    Ok(out)
+}
+
+/// Parameter names referenced in `text`.
+fn placeholders(text: &str) -> Vec<&str> {
+    let mut names = Vec::new();
+    let mut rest = text;
+    while let Some(start) = rest.find("{{") {
+        let Some(len) = rest[start + 2..].find("}}") else {
+            break;
+        };
+        names.push(rest[start + 2..start + 2 + len].trim());
+        rest = &rest[start + 4 + len..];
+    }
+    names
+}
+
+fn is_identifier(name: &str) -> bool {
+    !name.is_empty() && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn param(name: &str, default: Option<&str>) -> MacroParam {
+        MacroParam {
+            name: name.to_string(),
+            description: None,
+            default: default.map(str::to_string),
+        }
+    }
+
+    fn prompt(message: &str) -> MacroStep {
+        MacroStep::Prompt {
+            message: message.to_string(),
+            wait: true,
+        }
+    }
+
+    fn request(params: Vec<MacroParam>, steps: Vec<MacroStep>) -> SaveMacroRequest {
+        SaveMacroRequest {
+            name: "review".to_string(),
+            description: None,
+            params,
+            steps,
+        }
+    }
+
+    #[test]
+    fn test_validate() {
+        let config = MacrosConfig::default();
+        let ok = request(vec![param("file", None)], vec![prompt("Review {{ file }}")]);
+        assert!(validate(&ok, &config).is_ok());
+
+        let undeclared = request(vec![], vec![prompt("Review {{file}}")]);
+        assert!(validate(&undeclared, &config).unwrap_err().contains("file"));
+
+        let empty = request(vec![], vec![]);
+        assert!(validate(&empty, &config).is_err());
+
+        let duplicate = request(
+            vec![param("file", None), param("file", None)],
+            vec![prompt("x")],
+        );
+        assert!(validate(&duplicate, &config).is_err());
+
+        let bad_name = request(vec![param("a-b", None)], vec![prompt("x")]);
+        assert!(validate(&bad_name, &config).is_err());
+
+        let too_many = request(vec![], vec![prompt("x"); config.max_steps + 1]);
+        assert!(validate(&too_many, &config).is_err());
+    }
+
+    #[test]
+    fn test_resolve_steps() {
+        let user_macro = UserMacro {
+            id: "mac_1".to_string(),
+            user_id: "alice".to_string(),
+            name: "review".to_string(),
+            description: None,
+            params: vec![param("file", None), param("focus", Some("bugs"))],
+            steps: vec![prompt("Review {{file}} for {{focus}}. {not a param}")],
+            created_at: String::new(),
+            updated_at: String::new(),
+        };
+
+        let provided = HashMap::from([("file".to_string(), "main.rs".to_string())]);
+        assert_eq!(
+            resolve_steps(&user_macro, &provided).unwrap(),
+            vec![prompt("Review main.rs for bugs. {not a param}")]
+        );
+
+        assert!(resolve_steps(&user_macro, &HashMap::new()).is_err());
+        let unknown = HashMap::from([
+            ("file".to_string(), "main.rs".to_string()),
+            ("other".to_string(), "x".to_string()),
+        ]);
+        assert!(resolve_steps(&user_macro, &unknown).is_err());
+    }
+}
@@ -0,0 +1,202 @@
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+/// `[macros]` configuration.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MacrosConfig {
+    /// Let users define macros and run them against their sessions.
+    pub enabled: bool,
+    /// Most macros one user can keep.
+    pub max_macros_per_user: i64,
+    /// Most steps in one macro.
+    pub max_steps: usize,
+    /// Seconds a step may wait for the agent to finish before it fails.
+    pub step_timeout_secs: u64,
+}
+
+impl Default for MacrosConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            max_macros_per_user: 100,
+            max_steps: 50,
+            step_timeout_secs: 600,
+        }
+    }
+}
+
+/// A parameter referenced as `{{name}}` in step text.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MacroParam {
+    pub name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    /// Value used when the run does not pass one. Without a default the
+    /// parameter is required.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub default: Option<String>,
+}
+
+/// One command of a macro.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum MacroStep {
+    /// Send a prompt; with `wait`, until the agent is idle again.
+    Prompt {
+        message: String,
+        #[serde(default = "default_wait")]
+        wait: bool,
+    },
+    /// Queue a follow-up message for after the current run.
+    FollowUp { message: String },
+    /// Run a prompt template (`/name args`).
+    Template {
+        name: String,
+        #[serde(default)]
+        args: String,
+        #[serde(default = "default_wait")]
+        wait: bool,
+    },
+    /// Approve permission requests of a tool class for the rest of the run
+    /// (`*` approves every request).
+    Approve { tool_class: String },
+    /// Switch the session's model.
+    SetModel { provider: String, model_id: String },
+    /// Compact the conversation.
+    Compact {
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        instructions: Option<String>,
+    },
+}
+
+fn default_wait() -> bool {
+    true
+}
+
+impl MacroStep {
+    /// Step type as serialized.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Self::Prompt { .. } => "prompt",
+            Self::FollowUp { .. } => "follow_up",
+            Self::Template { .. } => "template",
+            Self::Approve { .. } => "approve",
+            Self::SetModel { .. } => "set_model",
+            Self::Compact { .. } => "compact",
+        }
+    }
+
+    /// Text fields that may contain `{{param}}` placeholders.
+    pub fn texts(&self) -> Vec<&str> {
+        match self {
+            Self::Prompt { message, .. } | Self::FollowUp { message } => vec![message.as_str()],
+            Self::Template { name, args, .. } => vec![name.as_str(), args.as_str()],
+            Self::Approve { tool_class } => vec![tool_class.as_str()],
+            Self::SetModel { provider, model_id } => vec![provider.as_str(), model_id.as_str()],
+            Self::Compact { instructions } => instructions.as_deref().into_iter().collect(),
+        }
+    }
+
+    /// Copy of the step with `f` applied to every text field.
+    pub fn map_texts<E>(&self, mut f: impl FnMut(&str) -> Result<String, E>) -> Result<Self, E> {
+        Ok(match self {
+            Self::Prompt { message, wait } => Self::Prompt {
+                message: f(message)?,
+                wait: *wait,
+            },
+            Self::FollowUp { message } => Self::FollowUp {
+                message: f(message)?,
+            },
+            Self::Template { name, args, wait } => Self::Template {
+                name: f(name)?,
+                args: f(args)?,
+                wait: *wait,
+            },
+            Self::Approve { tool_class } => Self::Approve {
+                tool_class: f(tool_class)?,
+            },
+            Self::SetModel { provider, model_id } => Self::SetModel {
+                provider: f(provider)?,
+                model_id: f(model_id)?,
+            },
+            Self::Compact { instructions } => Self::Compact {
+                instructions: instructions.as_deref().map(&mut f).transpose()?,
+            },
+        })
+    }
+}
+
+/// A stored macro.
+#[derive(Debug, Clone, Serialize)]
+pub struct UserMacro {
+    pub id: String,
+    pub user_id: String,
+    pub name: String,
+    pub description: Option<String>,
+    pub params: Vec<MacroParam>,
+    pub steps: Vec<MacroStep>,
+    pub created_at: String,
+    pub updated_at: String,
+}
+
+/// Request body for creating or replacing a macro.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SaveMacroRequest {
+    pub name: String,
+    #[serde(default)]
+    pub description: Option<String>,
+    #[serde(default)]
+    pub params: Vec<MacroParam>,
+    pub steps: Vec<MacroStep>,
+}
+
+/// Request body for running a macro.
+#[derive(Debug, Clone, Deserialize)]
+pub struct RunMacroRequest {
+    pub session_id: String,
+    /// Route to the shared workspace's runner, like the chat routes.
+    #[serde(default)]
+    pub shared_workspace_id: Option<String>,
+    #[serde(default)]
+    pub params: HashMap<String, String>,
+}
+
+/// Outcome of a step.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StepStatus {
+    Ok,
+    Failed,
+    /// Not run because an earlier step failed.
+    Skipped,
+}
+
+/// Result of one step of a run.
+#[derive(Debug, Clone, Serialize)]
+pub struct MacroStepResult {
+    pub index: usize,
+    #[serde(rename = "type")]
+    pub kind: &'static str,
+    pub status: StepStatus,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    /// Permission requests approved while the step ran.
+    #[serde(skip_serializing_if = "is_zero")]
+    pub approvals: u32,
+    pub duration_ms: u64,
+}
+
+fn is_zero(value: &u32) -> bool {
+    *value == 0
+}
+
+/// Result of running a macro. Steps stop at the first failure.
+#[derive(Debug, Clone, Serialize)]
+pub struct MacroRun {
+    pub macro_id: String,
+    pub session_id: String,
+    pub success: bool,
+    pub steps: Vec<MacroStepResult>,
+}
@@ -0,0 +1,227 @@
+use anyhow::{Context, Result};
+use sqlx::{FromRow, SqlitePool};
+
+use super::{MacroParam, MacroStep, SaveMacroRequest, UserMacro};
+
+const MACRO_COLUMNS: &str = "id, user_id, name, description, params, steps, created_at, updated_at";
+
+#[derive(Debug, Clone, FromRow)]
+struct MacroRow {
+    id: String,
+    user_id: String,
+    name: String,
+    description: Option<String>,
+    params: String,
+    steps: String,
+    created_at: String,
+    updated_at: String,
+}
+
+impl TryFrom<MacroRow> for UserMacro {
+    type Error = anyhow::Error;
+
+    fn try_from(row: MacroRow) -> Result<Self> {
+        let params: Vec<MacroParam> = serde_json::from_str(&row.params)
+            .with_context(|| format!("parsing params of macro {}", row.id))?;
+        let steps: Vec<MacroStep> = serde_json::from_str(&row.steps)
+            .with_context(|| format!("parsing steps of macro {}", row.id))?;
+        Ok(Self {
+            id: row.id,
+            user_id: row.user_id,
+            name: row.name,
+            description: row.description,
+            params,
+            steps,
+            created_at: row.created_at,
+            updated_at: row.updated_at,
+        })
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct MacroRepository {
+    pool: SqlitePool,
+}
+
+impl MacroRepository {
+    pub fn new(pool: SqlitePool) -> Self {
+        Self { pool }
+    }
+
+    fn generate_id() -> String {
+        format!("mac_{}", nanoid::nanoid!(12))
+    }
+
+    pub async fn create(&self, user_id: &str, request: &SaveMacroRequest) -> Result<UserMacro> {
+        let id = Self::generate_id();
+        sqlx::query(
+            r#"INSERT INTO user_macros (id, user_id, name, description, params, steps)
+               VALUES (?, ?, ?, ?, ?, ?)"#,
+        )
+        .bind(&id)
+        .bind(user_id)
+        .bind(&request.name)
+        .bind(&request.description)
+        .bind(serde_json::to_string(&request.params)?)
+        .bind(serde_json::to_string(&request.steps)?)
+        .execute(&self.pool)
+        .await
+        .context("insert macro")?;
+
+        self.get(&id)
+            .await?
+            .ok_or_else(|| anyhow::anyhow!("Macro not found after creation"))
+    }
+
+    pub async fn get(&self, id: &str) -> Result<Option<UserMacro>> {
+        let row = sqlx::query_as::<_, MacroRow>(&format!(
+            "SELECT {MACRO_COLUMNS} FROM user_macros WHERE id = ?"
+        ))
+        .bind(id)
+        .fetch_optional(&self.pool)
+        .await
+        .context("get macro")?;
+        row.map(TryInto::try_into).transpose()
+    }
+
+    /// A user's macros by name.
+    pub async fn list_for_user(&self, user_id: &str) -> Result<Vec<UserMacro>> {
+        let rows = sqlx::query_as::<_, MacroRow>(&format!(
+            "SELECT {MACRO_COLUMNS} FROM user_macros WHERE user_id = ? ORDER BY name, id"
+        ))
+        .bind(user_id)
+        .fetch_all(&self.pool)
+        .await
+        .context("list macros")?;
+        rows.into_iter().map(TryInto::try_into).collect()
+    }
+
+    pub async fn count_for_user(&self, user_id: &str) -> Result<i64> {
+        sqlx::query_scalar("SELECT COUNT(*) FROM user_macros WHERE user_id = ?")
+            .bind(user_id)
+            .fetch_one(&self.pool)
+            .await
+            .context("count macros")
+    }
+
+    /// Whether the user has another macro with this name.
+    pub async fn name_taken(
+        &self,
+        user_id: &str,
+        name: &str,
+        except_id: Option<&str>,
+    ) -> Result<bool> {
+        let id: Option<String> =
+            sqlx::query_scalar("SELECT id FROM user_macros WHERE user_id = ? AND name = ?")
+                .bind(user_id)
+                .bind(name)
+                .fetch_optional(&self.pool)
+                .await
+                .context("check macro name")?;
+        Ok(id.is_some_and(|id| Some(id.as_str()) != except_id))
+    }
+
+    pub async fn update(&self, id: &str, request: &SaveMacroRequest) -> Result<()> {
+        sqlx::query(
+            r#"UPDATE user_macros
+               SET name = ?, description = ?, params = ?, steps = ?, updated_at = datetime('now')
+               WHERE id = ?"#,
+        )
+        .bind(&request.name)
+        .bind(&request.description)
+        .bind(serde_json::to_string(&request.params)?)
+        .bind(serde_json::to_string(&request.steps)?)
+        .bind(id)
+        .execute(&self.pool)
+        .await
+        .context("update macro")?;
+        Ok(())
+    }
+
+    pub async fn delete(&self, id: &str) -> Result<bool> {
+        let result = sqlx::query("DELETE FROM user_macros WHERE id = ?")
+            .bind(id)
+            .execute(&self.pool)
+            .await
+            .context("delete macro")?;
+        Ok(result.rows_affected() > 0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::Database;
+
+    async fn repo() -> MacroRepository {
+        let db = Database::in_memory().await.unwrap();
+        sqlx::query("INSERT INTO users (id, username, email, display_name) VALUES (?, ?, ?, ?)")
+            .bind("alice")
+            .bind("alice")
+            .bind("alice@example.com")
+            .bind("alice")
+            .execute(db.pool())
+            .await
+            .unwrap();
+        MacroRepository::new(db.pool().clone())
+    }
+
+    fn request(name: &str) -> SaveMacroRequest {
+        SaveMacroRequest {
+            name: name.to_string(),
+            description: None,
+            params: vec![MacroParam {
+                name: "file".to_string(),
+                description: None,
+                default: Some("README.md".to_string()),
+            }],
+            steps: vec![
+                MacroStep::Approve {
+                    tool_class: "bash".to_string(),
+                },
+                MacroStep::Prompt {
+                    message: "Review {{file}}".to_string(),
+                    wait: true,
+                },
+            ],
+        }
+    }
+
+    #[tokio::test]
+    async fn test_macro_roundtrip() {
+        let repo = repo().await;
+        let created = repo.create("alice", &request("review")).await.unwrap();
+        assert_eq!(created.steps, request("review").steps);
+        assert_eq!(created.params[0].default.as_deref(), Some("README.md"));
+
+        repo.create("alice", &request("another")).await.unwrap();
+        let names: Vec<_> = repo
+            .list_for_user("alice")
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|m| m.name)
+            .collect();
+        assert_eq!(names, vec!["another", "review"]);
+        assert_eq!(repo.count_for_user("alice").await.unwrap(), 2);
+
+        assert!(repo.name_taken("alice", "review", None).await.unwrap());
+        assert!(
+            !repo
+                .name_taken("alice", "review", Some(&created.id))
+                .await
+                .unwrap()
+        );
+        assert!(repo.create("alice", &request("review")).await.is_err());
+
+        let mut renamed = request("review all");
+        renamed.steps.truncate(1);
+        repo.update(&created.id, &renamed).await.unwrap();
+        let updated = repo.get(&created.id).await.unwrap().unwrap();
+        assert_eq!(updated.name, "review all");
+        assert_eq!(updated.steps.len(), 1);
+
+        assert!(repo.delete(&created.id).await.unwrap());
+        assert!(!repo.delete(&created.id).await.unwrap());
+    }
+}
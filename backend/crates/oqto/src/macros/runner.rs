@@ -0,0 +1,411 @@
+use std::collections::HashSet;
+use std::time::{Duration, Instant};
+
+use anyhow::{Result, bail};
+use async_trait::async_trait;
+use oqto_protocol::events::{EventPayload, InputRequest};
+use oqto_runner::client::{PiSubscription, PiSubscriptionEvent, RunnerClient};
+
+use super::{MacroStep, MacroStepResult, StepStatus};
+
+/// The commands a macro can issue against a session.
+#[async_trait]
+pub trait MacroAgent: Send {
+    async fn prompt(&mut self, message: &str) -> Result<()>;
+    async fn follow_up(&mut self, message: &str) -> Result<()>;
+    async fn set_model(&mut self, provider: &str, model_id: &str) -> Result<()>;
+    async fn compact(&mut self, instructions: Option<&str>) -> Result<()>;
+    /// Confirm a pending permission or confirm request.
+    async fn approve(&mut self, request_id: &str) -> Result<()>;
+    /// Next event of the session. None when the event stream ended.
+    async fn next_event(&mut self) -> Option<EventPayload>;
+}
+
+/// [`MacroAgent`] for a session on a runner.
+pub struct RunnerAgent {
+    runner: RunnerClient,
+    session_id: String,
+    events: PiSubscription,
+}
+
+impl RunnerAgent {
+    /// Subscribe to the session's events, so none are missed between a
+    /// step's command and its wait.
+    pub async fn connect(runner: RunnerClient, session_id: &str) -> Result<Self> {
+        let events = runner.agent_subscribe(session_id).await?;
+        Ok(Self {
+            runner,
+            session_id: session_id.to_string(),
+            events,
+        })
+    }
+}
+
+#[async_trait]
+impl MacroAgent for RunnerAgent {
+    async fn prompt(&mut self, message: &str) -> Result<()> {
+        self.runner
+            .agent_prompt(&self.session_id, message, None)
+            .await
+    }
+
+    async fn follow_up(&mut self, message: &str) -> Result<()> {
+        self.runner
+            .agent_follow_up(&self.session_id, message, None)
+            .await
+    }
+
+    async fn set_model(&mut self, provider: &str, model_id: &str) -> Result<()> {
+        self.runner
+            .agent_set_model(&self.session_id, provider, model_id)
+            .await
+            .map(|_| ())
+    }
+
+    async fn compact(&mut self, instructions: Option<&str>) -> Result<()> {
+        self.runner
+            .agent_compact(&self.session_id, instructions)
+            .await
+    }
+
+    async fn approve(&mut self, request_id: &str) -> Result<()> {
+        self.runner
+            .agent_extension_ui_response(&self.session_id, request_id, None, Some(true), None)
+            .await
+    }
+
+    async fn next_event(&mut self) -> Option<EventPayload> {
+        loop {
+            // `next` yields None for lines it skips, not for the end.
+            let Some(event) = self.events.next().await else {
+                continue;
+            };
+            match event {
+                PiSubscriptionEvent::Event(event) => return Some(event.payload),
+                PiSubscriptionEvent::End { .. } => return None,
+                PiSubscriptionEvent::Error { message, .. } => {
+                    tracing::warn!(session_id = %self.session_id, "macro event stream error: {message}");
+                    return None;
+                }
+            }
+        }
+    }
+}
+
+/// Run the steps in order. After the first failure the remaining steps are
+/// reported as skipped.
+pub async fn run_steps(
+    agent: &mut dyn MacroAgent,
+    steps: &[MacroStep],
+    step_timeout: Duration,
+) -> Vec<MacroStepResult> {
+    let mut approved = HashSet::new();
+    let mut failed = false;
+    let mut results = Vec::with_capacity(steps.len());
+
+    for (index, step) in steps.iter().enumerate() {
+        let mut result = MacroStepResult {
+            index,
+            kind: step.kind(),
+            status: StepStatus::Skipped,
+            error: None,
+            approvals: 0,
+            duration_ms: 0,
+        };
+        if failed {
+            results.push(result);
+            continue;
+        }
+
+        let started = Instant::now();
+        let outcome = run_step(
+            agent,
+            step,
+            &mut approved,
+            step_timeout,
+            &mut result.approvals,
+        )
+        .await;
+        result.duration_ms = started.elapsed().as_millis() as u64;
+        match outcome {
+            Ok(()) => result.status = StepStatus::Ok,
+            Err(e) => {
+                result.status = StepStatus::Failed;
+                result.error = Some(format!("{e:#}"));
+                failed = true;
+            }
+        }
+        results.push(result);
+    }
+    results
+}
+
+async fn run_step(
+    agent: &mut dyn MacroAgent,
+    step: &MacroStep,
+    approved: &mut HashSet<String>,
+    step_timeout: Duration,
+    approvals: &mut u32,
+) -> Result<()> {
+    match step {
+        MacroStep::Prompt { message, wait } => {
+            agent.prompt(message).await?;
+            if *wait {
+                wait_idle(agent, approved, step_timeout, approvals).await?;
+            }
+        }
+        MacroStep::Template { name, args, wait } => {
+            let command = format!("/{} {}", name.trim_start_matches('/'), args);
+            agent.prompt(command.trim_end()).await?;
+            if *wait {
+                wait_idle(agent, approved, step_timeout, approvals).await?;
+            }
+        }
+        MacroStep::FollowUp { message } => agent.follow_up(message).await?,
+        MacroStep::Approve { tool_class } => {
+            approved.insert(tool_class.to_lowercase());
+        }
+        MacroStep::SetModel { provider, model_id } => agent.set_model(provider, model_id).await?,
+        MacroStep::Compact { instructions } => agent.compact(instructions.as_deref()).await?,
+    }
+    Ok(())
+}
+
+/// Wait until the agent has worked and is idle again, approving matching
+/// permission requests on the way.
+async fn wait_idle(
+    agent: &mut dyn MacroAgent,
+    approved: &HashSet<String>,
+    step_timeout: Duration,
+    approvals: &mut u32,
+) -> Result<()> {
+    let deadline = tokio::time::Instant::now() + step_timeout;
+    let mut working = false;
+    loop {
+        let Ok(event) = tokio::time::timeout_at(deadline, agent.next_event()).await else {
+            bail!("agent did not finish within {}s", step_timeout.as_secs());
+        };
+        match event {
+            None => bail!("session event stream ended"),
+            Some(EventPayload::AgentWorking { .. }) => working = true,
+            Some(EventPayload::AgentIdle { .. }) if working => return Ok(()),
+            Some(EventPayload::AgentError { error, .. }) => bail!("agent error: {error}"),
+            Some(EventPayload::SessionClosed { .. }) => bail!("session closed"),
+            Some(EventPayload::AgentInputNeeded { request }) => {
+                if let Some(request_id) = approvable(&request, approved) {
+                    agent.approve(request_id).await?;
+                    *approvals += 1;
+                }
+            }
+            Some(_) => {}
+        }
+    }
+}
+
+/// Request id of a permission or confirm request covered by an approve
+/// step. Other requests are left for the user.
+fn approvable<'a>(request: &'a InputRequest, approved: &HashSet<String>) -> Option<&'a str> {
+    if approved.is_empty() {
+        return None;
+    }
+    let (request_id, title, tool) = match request {
+        InputRequest::Permission {
+            request_id,
+            title,
+            metadata,
+            ..
+        } => {
+            let tool = metadata.as_ref().and_then(|m| {
+                ["tool", "tool_name", "class"]
+                    .iter()
+                    .find_map(|key| m.get(key).and_then(|v| v.as_str()))
+            });
+            (request_id, title, tool)
+        }
+        InputRequest::Confirm {
+            request_id, title, ..
+        } => (request_id, title, None),
+        InputRequest::Select { .. } | InputRequest::Input { .. } => return None,
+    };
+
+    let title = title.to_lowercase();
+    let matches = approved.iter().any(|class| {
+        class == "*"
+            || tool.is_some_and(|tool| tool.eq_ignore_ascii_case(class))
+            || title.contains(class.as_str())
+    });
+    matches.then_some(request_id.as_str())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::VecDeque;
+
+    use oqto_protocol::events::AgentPhase;
+
+    use super::*;
+
+    #[derive(Default)]
+    struct FakeAgent {
+        events: VecDeque<EventPayload>,
+        sent: Vec<String>,
+        approved: Vec<String>,
+        fail_prompts: bool,
+    }
+
+    #[async_trait]
+    impl MacroAgent for FakeAgent {
+        async fn prompt(&mut self, message: &str) -> Result<()> {
+            if self.fail_prompts {
+                bail!("runner unavailable");
+            }
+            self.sent.push(message.to_string());
+            Ok(())
+        }
+
+        async fn follow_up(&mut self, message: &str) -> Result<()> {
+            self.sent.push(format!("follow_up:{message}"));
+            Ok(())
+        }
+
+        async fn set_model(&mut self, provider: &str, model_id: &str) -> Result<()> {
+            self.sent.push(format!("model:{provider}/{model_id}"));
+            Ok(())
+        }
+
+        async fn compact(&mut self, _instructions: Option<&str>) -> Result<()> {
+            self.sent.push("compact".to_string());
+            Ok(())
+        }
+
+        async fn approve(&mut self, request_id: &str) -> Result<()> {
+            self.approved.push(request_id.to_string());
+            Ok(())
+        }
+
+        async fn next_event(&mut self) -> Option<EventPayload> {
+            self.events.pop_front()
+        }
+    }
+
+    fn working() -> EventPayload {
+        EventPayload::AgentWorking {
+            phase: AgentPhase::Generating,
+            detail: None,
+        }
+    }
+
+    fn idle() -> EventPayload {
+        EventPayload::AgentIdle {
+            message_version: None,
+        }
+    }
+
+    fn permission(request_id: &str, tool: &str) -> EventPayload {
+        EventPayload::AgentInputNeeded {
+            request: InputRequest::Permission {
+                request_id: request_id.to_string(),
+                title: format!("Allow {tool}?"),
+                description: None,
+                metadata: Some(serde_json::json!({ "tool": tool })),
+            },
+        }
+    }
+
+    const TIMEOUT: Duration = Duration::from_secs(5);
+
+    #[tokio::test]
+    async fn test_run_steps_approves_matching_requests() {
+        let mut agent = FakeAgent {
+            events: VecDeque::from([
+                working(),
+                permission("r1", "bash"),
+                permission("r2", "write"),
+                idle(),
+                working(),
+                idle(),
+            ]),
+            ..Default::default()
+        };
+        let steps = vec![
+            MacroStep::Approve {
+                tool_class: "Bash".to_string(),
+            },
+            MacroStep::Prompt {
+                message: "fix it".to_string(),
+                wait: true,
+            },
+            MacroStep::Template {
+                name: "review".to_string(),
+                args: "src".to_string(),
+                wait: true,
+            },
+            MacroStep::SetModel {
+                provider: "anthropic".to_string(),
+                model_id: "m".to_string(),
+            },
+        ];
+
+        let results = run_steps(&mut agent, &steps, TIMEOUT).await;
+        assert!(results.iter().all(|r| r.status == StepStatus::Ok));
+        assert_eq!(results[1].approvals, 1);
+        assert_eq!(agent.approved, vec!["r1"]);
+        assert_eq!(
+            agent.sent,
+            vec!["fix it", "/review src", "model:anthropic/m"]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_run_steps_stops_at_first_failure() {
+        let mut agent = FakeAgent {
+            events: VecDeque::from([
+                working(),
+                EventPayload::AgentError {
+                    error: "overloaded".to_string(),
+                    recoverable: true,
+                    phase: None,
+                },
+            ]),
+            ..Default::default()
+        };
+        let steps = vec![
+            MacroStep::Prompt {
+                message: "go".to_string(),
+                wait: true,
+            },
+            MacroStep::Compact { instructions: None },
+        ];
+
+        let results = run_steps(&mut agent, &steps, TIMEOUT).await;
+        assert_eq!(results[0].status, StepStatus::Failed);
+        assert!(results[0].error.as_deref().unwrap().contains("overloaded"));
+        assert_eq!(results[1].status, StepStatus::Skipped);
+        assert_eq!(agent.sent, vec!["go"]);
+
+        let mut agent = FakeAgent {
+            fail_prompts: true,
+            ..Default::default()
+        };
+        let results = run_steps(&mut agent, &steps[..1], TIMEOUT).await;
+        assert_eq!(results[0].status, StepStatus::Failed);
+    }
+
+    #[test]
+    fn test_approvable() {
+        let approved = HashSet::from(["bash".to_string()]);
+        let EventPayload::AgentInputNeeded { request } = permission("r1", "bash") else {
+            unreachable!()
+        };
+        assert_eq!(approvable(&request, &approved), Some("r1"));
+        assert_eq!(approvable(&request, &HashSet::new()), None);
+
+        let select = InputRequest::Select {
+            request_id: "r2".to_string(),
+            title: "bash?".to_string(),
+            options: vec![],
+            timeout: None,
+        };
+        assert_eq!(approvable(&select, &HashSet::from(["*".to_string()])), None);
+    }
+}
@@ -3,7 +3,6 @@ use std::env;
 use std::fmt;
 use std::fs;
 use std::io::{self, IsTerminal, Write};
-use std::net::SocketAddr;
 use std::path::{Path, PathBuf};
 use std::sync::Arc;
 use std::time::Duration;
@@ -15,7 +14,6 @@ use config::{Config, Environment, File, FileFormat};
 
 use log::{LevelFilter, debug, error, info, warn};
 use serde::{Deserialize, Serialize};
-use tokio::net::TcpListener;
 use tower_http::services::{ServeDir, ServeFile};
 
 #[cfg(unix)]
@@ -31,6 +29,7 @@ use std::os::unix::io::AsRawFd;
 #[cfg(unix)]
 use tokio::net::{UnixListener, UnixStream};
 
+mod admin_overview;
 mod agent_browser;
 mod bus;
 
@@ -38,34 +37,71 @@ mod api;
 mod api_keys;
 mod audit;
 mod auth;
+mod bookmarks;
 mod canon;
+mod capabilities;
+mod config_reload;
 mod container;
+mod crash_bundles;
 mod db;
 mod eavs;
 mod feedback;
+mod git_credentials;
 mod history;
 mod hstry;
 mod identity;
+mod impersonation;
 mod invite;
 mod local;
+mod macros;
 mod markdown;
+mod mdns;
+mod memory_promotion;
+mod message_annotations;
+mod net;
 mod observability;
 mod onboarding;
 mod oqto_log;
+mod outbox;
 mod pi;
+mod priority_lanes;
+mod private_messages;
 // pi_workspace removed -- JSONL scanning replaced by hstry-only session listing
+mod project_cards;
 mod projects;
+mod prompt_drafts;
+mod redaction;
+mod registration;
+mod remote_config;
 mod runner;
+mod scheduler;
+mod secrets;
 mod session;
+mod session_drafts;
+mod session_events;
+mod session_shares;
+mod session_tags;
 mod session_target;
 mod session_ui;
 mod settings;
 mod shared_workspace;
+mod siem;
+mod status;
+mod storage;
+mod template_tests;
 mod templates;
+mod tool_usage;
+mod triggers;
 mod user;
+mod user_env;
 mod user_plane;
+mod voice_health;
+mod vuln_scan;
+mod webhooks;
 mod wordlist;
 mod workspace;
+mod workspace_access;
+mod workspace_backups;
 mod ws;
 
 const APP_NAME: &str = "oqto";
@@ -75,6 +111,11 @@ use crate::session_target::{SessionTargetRecord, SessionTargetScope};
 use crate::session_ui::SessionAutoAttachMode;
 use crate::user::UserListQuery;
 
+/// Handle for changing the tracing filter when `logging.level` is reloaded.
+static LOG_FILTER: std::sync::OnceLock<
+    tracing_subscriber::reload::Handle<tracing_subscriber::EnvFilter, tracing_subscriber::Registry>,
+> = std::sync::OnceLock::new();
+
 fn main() {
     if let Err(err) = try_main() {
         let _ = writeln!(io::stderr(), "{err:?}");
@@ -83,7 +124,19 @@ fn main() {
 }
 
 #[tokio::main]
-async fn async_main(ctx: RuntimeContext, cmd: ServeCommand) -> Result<()> {
+async fn async_main(mut ctx: RuntimeContext, cmd: ServeCommand) -> Result<()> {
+    if let Some(source) = ctx.remote_config.clone() {
+        match source.refresh(&reqwest::Client::new()).await {
+            Ok(Some(_)) => {
+                info!("Fetched new remote config bundle");
+                ctx = RuntimeContext::new(ctx.common.clone())?;
+            }
+            Ok(None) => debug!("Remote config bundle unchanged"),
+            Err(e) => {
+                warn!("Remote config fetch failed, starting with cached or local config: {e:#}")
+            }
+        }
+    }
     handle_serve(&ctx, cmd).await
 }
 
@@ -97,6 +150,11 @@ async fn async_search(cmd: SearchCommand) -> Result<()> {
     handle_search(cmd).await
 }
 
+#[tokio::main]
+async fn async_feedback(ctx: RuntimeContext, cmd: FeedbackCommand) -> Result<()> {
+    handle_feedback(&ctx, cmd).await
+}
+
 fn try_main() -> Result<()> {
     let cli = Cli::parse();
 
@@ -111,6 +169,7 @@ fn try_main() -> Result<()> {
         Command::InviteCodes { command } => async_invite_codes(ctx, command),
         Command::Runner { command } => handle_runner(&ctx, command),
         Command::Search { command } => async_search(command),
+        Command::Feedback { command } => async_feedback(ctx, command),
         Command::Completions { shell } => handle_completions(shell),
     }
 }
@@ -131,9 +190,18 @@ struct Cli {
 
 #[derive(Debug, Clone, Args)]
 struct CommonOpts {
-    /// Override the config file path
-    #[arg(long, value_name = "PATH", global = true)]
+    /// Override the config file path, or fetch a signed config bundle from
+    /// an https:// URL (layered over the default config file)
+    #[arg(long, value_name = "PATH|URL", global = true)]
     config: Option<PathBuf>,
+    /// Minisign public key remote config bundles must be signed with
+    #[arg(
+        long,
+        value_name = "KEY",
+        env = "OQTO_CONFIG_PUBLIC_KEY",
+        global = true
+    )]
+    config_public_key: Option<String>,
     /// Reduce output to only errors
     #[arg(short, long, action = clap::ArgAction::SetTrue, global = true)]
     quiet: bool,
@@ -211,6 +279,11 @@ enum Command {
         #[command(subcommand)]
         command: SearchCommand,
     },
+    /// Maintain archived user feedback
+    Feedback {
+        #[command(subcommand)]
+        command: FeedbackCommand,
+    },
     /// Generate shell completions
     Completions {
         #[arg(value_enum)]
@@ -220,9 +293,9 @@ enum Command {
 
 #[derive(Debug, Clone, Args)]
 struct ServeCommand {
-    /// Host address to bind to
-    #[arg(long, default_value = "0.0.0.0")]
-    host: String,
+    /// Host address to bind to, e.g. `0.0.0.0` or `::` (default: `[server] host`)
+    #[arg(long)]
+    host: Option<String>,
     /// Port to listen on
     #[arg(short, long, default_value = "8080")]
     port: u16,
@@ -266,8 +339,22 @@ enum InviteCodesCommand {
     Generate(InviteCodesGenerateCommand),
     /// List existing invite codes
     List(InviteCodesListCommand),
-    /// Revoke an invite code
+    /// Revoke an invite code, or every code with a note/prefix
     Revoke(InviteCodesRevokeCommand),
+    /// Export invite codes as CSV or JSON
+    Export(InviteCodesExportCommand),
+    /// Import pre-generated invite codes from a CSV file
+    Import(InviteCodesImportCommand),
+    /// Show who redeemed an invite code and when
+    History(InviteCodesHistoryCommand),
+}
+
+#[derive(Debug, Subcommand)]
+enum FeedbackCommand {
+    /// Anonymize retained raw feedback again and overwrite the archived copies
+    Reprocess,
+    /// Delete raw feedback older than `[feedback] raw_retention_days`
+    Prune,
 }
 
 #[derive(Debug, Subcommand)]
@@ -352,8 +439,50 @@ struct InviteCodesListCommand {
 }
 
 #[derive(Debug, Clone, Args)]
+#[command(group(clap::ArgGroup::new("target").required(true).multiple(true).args(["code_id", "note", "prefix"])))]
 struct InviteCodesRevokeCommand {
     /// ID of the invite code to revoke
+    #[arg(conflicts_with_all = ["note", "prefix"])]
+    code_id: Option<String>,
+    /// Revoke every usable code with this note
+    #[arg(long)]
+    note: Option<String>,
+    /// Revoke every usable code starting with this prefix
+    #[arg(long)]
+    prefix: Option<String>,
+}
+
+#[derive(Debug, Clone, Args)]
+struct InviteCodesExportCommand {
+    /// Output format (csv, json)
+    #[arg(long, default_value = "csv")]
+    format: String,
+    /// Filter by validity (valid, invalid, all)
+    #[arg(short, long, default_value = "all")]
+    filter: String,
+    /// Write to this file instead of stdout
+    #[arg(short, long)]
+    output: Option<PathBuf>,
+}
+
+#[derive(Debug, Clone, Args)]
+struct InviteCodesImportCommand {
+    /// CSV file with a header row and a `code` column ("-" for stdin)
+    file: PathBuf,
+    /// Uses per code where the file does not say
+    #[arg(short = 'u', long, default_value = "1")]
+    uses_per_code: i32,
+    /// Note for codes without one
+    #[arg(short, long)]
+    note: Option<String>,
+    /// Admin user ID importing the codes
+    #[arg(long, default_value = "usr_admin")]
+    admin_id: String,
+}
+
+#[derive(Debug, Clone, Args)]
+struct InviteCodesHistoryCommand {
+    /// ID of the invite code
     code_id: String,
 }
 
@@ -362,17 +491,29 @@ struct RuntimeContext {
     common: CommonOpts,
     paths: AppPaths,
     config: AppConfig,
+    /// Signed remote config bundle, when one is configured.
+    remote_config: Option<remote_config::RemoteConfigSource>,
 }
 
 impl RuntimeContext {
     fn new(common: CommonOpts) -> Result<Self> {
-        let mut paths = AppPaths::discover(common.config.clone())?;
-        let config = load_or_init_config(&mut paths, &common)?;
+        // `--config https://...` names a remote bundle; the local config file
+        // stays at its default location.
+        let (config_override, remote_url) = match common.config.clone() {
+            Some(path) if path.to_str().is_some_and(remote_config::is_remote_url) => {
+                (None, path.to_str().map(str::to_string))
+            }
+            other => (other, None),
+        };
+        let mut paths = AppPaths::discover(config_override)?;
+        let (config, remote_config) =
+            load_or_init_config(&mut paths, &common, remote_url.as_deref())?;
         let paths = paths.apply_overrides(&config)?;
         let ctx = Self {
             common,
             paths,
             config,
+            remote_config,
         };
         ctx.ensure_directories()?;
         Ok(ctx)
@@ -386,18 +527,10 @@ impl RuntimeContext {
             return Ok(());
         }
 
-        // Determine filter level
-        let level = match self.effective_log_level() {
-            LevelFilter::Off => "off",
-            LevelFilter::Error => "error",
-            LevelFilter::Warn => "warn",
-            LevelFilter::Info => "info",
-            LevelFilter::Debug => "debug",
-            LevelFilter::Trace => "trace",
-        };
-
         let env_filter = EnvFilter::try_from_default_env()
-            .unwrap_or_else(|_| EnvFilter::new(format!("oqto={level},tower_http={level}")));
+            .unwrap_or_else(|_| log_filter(self.effective_log_level()));
+        let (env_filter, filter_handle) = tracing_subscriber::reload::Layer::new(env_filter);
+        LOG_FILTER.set(filter_handle).ok();
 
         // Use JSON output if --json flag is set, otherwise pretty format
         if self.common.json {
@@ -443,13 +576,28 @@ impl RuntimeContext {
             LevelFilter::Debug
         } else {
             match self.common.verbose {
-                0 => LevelFilter::Info,
+                0 => self
+                    .config
+                    .logging
+                    .level
+                    .parse()
+                    .unwrap_or(LevelFilter::Info),
                 1 => LevelFilter::Debug,
                 _ => LevelFilter::Trace,
             }
         }
     }
 
+    /// Whether the log level comes from the command line or `RUST_LOG`, which
+    /// a reloaded `logging.level` does not override.
+    fn log_level_pinned(&self) -> bool {
+        self.common.quiet
+            || self.common.trace
+            || self.common.debug
+            || self.common.verbose > 0
+            || env::var_os("RUST_LOG").is_some()
+    }
+
     fn ensure_directories(&self) -> Result<()> {
         if self.common.dry_run {
             info!(
@@ -547,18 +695,98 @@ struct AppConfig {
     server: ServerConfig,
     /// Onboarding templates configuration.
     onboarding_templates: templates::OnboardingTemplatesConfig,
+    /// Admin script run once in each user's first workspace.
+    bootstrap_script: onboarding::BootstrapScriptConfig,
     /// sldr configuration.
     sldr: SldrConfig,
     /// hstry (chat history) configuration.
     hstry: HstryConfig,
     /// Feedback collection configuration.
     feedback: feedback::FeedbackConfig,
+    /// Agent tool usage statistics configuration.
+    tool_usage: tool_usage::ToolUsageConfig,
+    /// Per-session and per-model spend attribution.
+    usage: eavs::UsageConfig,
+    /// Per-session spend budgets.
+    budget: eavs::BudgetConfig,
+    /// Dev server preview proxy configuration.
+    dev_proxy: api::proxy::DevProxyConfig,
+    /// Live CPU/memory/GPU usage of running sessions.
+    resource_telemetry: session::ResourceTelemetryConfig,
+    /// Export and import of user environments between servers.
+    user_env: user_env::UserEnvConfig,
+    /// Database integrity checks and automatic restore.
+    db_integrity: db::DbIntegrityConfig,
+    /// Optional Postgres database for tables shared across replicas.
+    db: db::DbConfig,
+    /// Opt-in per-workspace encryption at rest.
+    workspace_encryption: workspace::encryption::WorkspaceEncryptionConfig,
+    /// Public status page (`GET /api/status`).
+    status_page: status::StatusPageConfig,
+    /// Time-boxed access to directories in other users' workspaces.
+    workspace_access: workspace_access::WorkspaceAccessConfig,
+    /// Long-poll event API for clients without WebSocket/SSE.
+    long_poll: session_events::LongPollConfig,
+    /// Scheduler run history and downtime catch-up.
+    scheduler: scheduler::SchedulerConfig,
+    /// Promoting session findings into mmry, and agent suggestions.
+    memory_promotion: memory_promotion::MemoryPromotionConfig,
+    /// Dependency vulnerability scans of workspaces.
+    vulnerability_scan: vuln_scan::VulnerabilityScanConfig,
+    /// Export of audit and authentication events to a SIEM.
+    siem: siem::SiemConfig,
+    /// Agent-composed emails and Slack messages, sent only after approval.
+    outbox: outbox::OutboxConfig,
+    /// Sessions started by webhooks and emails.
+    triggers: triggers::TriggersConfig,
+    /// Self-service registration without invite codes.
+    registration: registration::RegistrationConfig,
+    /// Replay of agent events missed while a WebSocket was reconnecting.
+    event_replay: ws::EventReplayConfig,
+    /// Reconnect hints and pacing of session resumes after restarts.
+    reconnect: ws::ReconnectConfig,
+    /// Incremental, deduplicated workspace backups.
+    workspace_backups: workspace_backups::WorkspaceBackupsConfig,
+    /// Admins acting as users with their consent.
+    impersonation: impersonation::ImpersonationConfig,
+    /// Automatic session tagging from prompt classification.
+    session_tags: session_tags::SessionTagsConfig,
+    /// Signed remote config bundle for fleet deployments.
+    config: remote_config::RemoteConfigSettings,
+    /// Prometheus metrics endpoint.
+    metrics: observability::metrics::MetricsConfig,
+    /// Object storage for feedback archives and crash bundles.
+    storage: storage::StorageConfig,
+    /// User-defined macros run against sessions.
+    macros: macros::MacrosConfig,
+    /// Throttling of background jobs while users are chatting.
+    priority_lanes: priority_lanes::PriorityLanesConfig,
+    /// mDNS advertisement for discovery by the desktop app.
+    mdns: mdns::MdnsConfig,
+    /// Per-IP and per-user request rate limits.
+    rate_limit: api::RateLimitConfig,
+    /// Runner hosts new sessions are scheduled across (`[[runners]]`).
+    runners: Vec<runner::federation::RunnerHostConfig>,
+    /// Health checking and failover of runner hosts.
+    runner_federation: runner::federation::RunnerFederationConfig,
+    /// Endpoints session lifecycle events are POSTed to (`[[webhooks]]`).
+    webhooks: Vec<webhooks::WebhookConfig>,
+    /// Sealed agent secrets injected into sessions.
+    secrets: secrets::SecretsConfig,
 }
 
 /// Server configuration.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(default)]
 struct ServerConfig {
+    /// Address to bind to when `--host` is not given (default: "0.0.0.0").
+    /// Use "::" to listen on IPv6.
+    host: String,
+    /// Accept IPv4 connections on an IPv6 bind (default: true).
+    dual_stack: bool,
+    /// Reverse proxies whose `X-Forwarded-For` names the client (default:
+    /// loopback). Empty trusts none.
+    trusted_proxies: Vec<std::net::IpAddr>,
     /// Maximum file upload size in megabytes (default: 100).
     max_upload_size_mb: usize,
     /// Optional admin Unix socket path for local CLI access.
@@ -569,6 +797,12 @@ impl Default for ServerConfig {
     fn default() -> Self {
         let admin_socket_path = default_admin_socket_path();
         Self {
+            host: "0.0.0.0".to_string(),
+            dual_stack: true,
+            trusted_proxies: vec![
+                std::net::Ipv4Addr::LOCALHOST.into(),
+                std::net::Ipv6Addr::LOCALHOST.into(),
+            ],
             max_upload_size_mb: 100,
             admin_socket_path,
         }
@@ -629,6 +863,12 @@ struct RunnerConfig {
     /// Socket directory pattern for per-user runner sockets.
     /// Default: /run/user/{uid}/oqto-runner.sock
     socket_pattern: Option<String>,
+    /// Wire protocol to runners: json (default) or grpc. Runners must be
+    /// started with a gRPC-capable build; remote runners also need
+    /// `--grpc-listen`.
+    transport: oqto_runner::client::Transport,
+    /// TLS for gRPC connections to remote runners.
+    grpc: oqto_runner::grpc::GrpcTlsConfig,
 }
 
 impl AppConfig {
@@ -662,8 +902,44 @@ impl Default for AppConfig {
             agent_browser: agent_browser::AgentBrowserConfig::default(),
             server: ServerConfig::default(),
             onboarding_templates: templates::OnboardingTemplatesConfig::default(),
+            bootstrap_script: onboarding::BootstrapScriptConfig::default(),
             hstry: HstryConfig::default(),
             feedback: feedback::FeedbackConfig::default(),
+            tool_usage: tool_usage::ToolUsageConfig::default(),
+            usage: eavs::UsageConfig::default(),
+            budget: eavs::BudgetConfig::default(),
+            dev_proxy: api::proxy::DevProxyConfig::default(),
+            resource_telemetry: session::ResourceTelemetryConfig::default(),
+            user_env: user_env::UserEnvConfig::default(),
+            db_integrity: db::DbIntegrityConfig::default(),
+            db: db::DbConfig::default(),
+            workspace_encryption: workspace::encryption::WorkspaceEncryptionConfig::default(),
+            status_page: status::StatusPageConfig::default(),
+            workspace_access: workspace_access::WorkspaceAccessConfig::default(),
+            long_poll: session_events::LongPollConfig::default(),
+            scheduler: scheduler::SchedulerConfig::default(),
+            memory_promotion: memory_promotion::MemoryPromotionConfig::default(),
+            vulnerability_scan: vuln_scan::VulnerabilityScanConfig::default(),
+            siem: siem::SiemConfig::default(),
+            outbox: outbox::OutboxConfig::default(),
+            triggers: triggers::TriggersConfig::default(),
+            registration: registration::RegistrationConfig::default(),
+            event_replay: ws::EventReplayConfig::default(),
+            reconnect: ws::ReconnectConfig::default(),
+            workspace_backups: workspace_backups::WorkspaceBackupsConfig::default(),
+            impersonation: impersonation::ImpersonationConfig::default(),
+            session_tags: session_tags::SessionTagsConfig::default(),
+            config: remote_config::RemoteConfigSettings::default(),
+            metrics: observability::metrics::MetricsConfig::default(),
+            storage: storage::StorageConfig::default(),
+            macros: macros::MacrosConfig::default(),
+            priority_lanes: priority_lanes::PriorityLanesConfig::default(),
+            mdns: mdns::MdnsConfig::default(),
+            rate_limit: api::RateLimitConfig::default(),
+            runners: Vec::new(),
+            runner_federation: runner::federation::RunnerFederationConfig::default(),
+            webhooks: Vec::new(),
+            secrets: secrets::SecretsConfig::default(),
         }
     }
 }
@@ -758,6 +1034,12 @@ pub struct VoiceConfig {
     /// Set to 0 to disable backoff (words accumulate forever until threshold).
     /// Default: 5000
     pub interrupt_backoff_ms: u32,
+    /// How often the STT and TTS endpoints are probed, in seconds.
+    /// Default: 15
+    pub health_check_interval_secs: u64,
+    /// Failed probes in a row before an endpoint counts as unreachable.
+    /// Default: 2
+    pub health_failure_threshold: u32,
     /// Per-visualizer voice/speed settings.
     /// Keys are visualizer IDs (e.g., "orb", "kitt"), values are VisualizerVoice.
     #[serde(default)]
@@ -793,6 +1075,8 @@ impl Default for VoiceConfig {
             default_visualizer: "orb".to_string(),
             interrupt_word_count: 2,
             interrupt_backoff_ms: 5000,
+            health_check_interval_secs: 15,
+            health_failure_threshold: 2,
             visualizer_voices: [
                 (
                     "orb".to_string(),
@@ -827,6 +1111,8 @@ struct SessionUiConfig {
     max_concurrent_sessions: i64,
     /// Idle timeout in minutes before stopping a session.
     idle_timeout_minutes: i64,
+    /// What happens to idle sessions: "stop" or "hibernate".
+    idle_action: session::IdleAction,
     /// Idle cleanup check interval in seconds.
     idle_check_interval_seconds: u64,
     /// Number of recent sessions to prefetch chat messages for.
@@ -840,6 +1126,7 @@ impl Default for SessionUiConfig {
             auto_attach_scan: false,
             max_concurrent_sessions: session::SessionService::DEFAULT_MAX_CONCURRENT_SESSIONS,
             idle_timeout_minutes: session::SessionService::DEFAULT_IDLE_TIMEOUT_MINUTES,
+            idle_action: session::IdleAction::Stop,
             idle_check_interval_seconds: 5 * 60,
             chat_prefetch_limit: 8,
         }
@@ -967,6 +1254,8 @@ struct LoggingConfig {
     file: Option<String>,
     audit_enabled: bool,
     audit_file: Option<String>,
+    /// Days to keep audit events; unset keeps them forever.
+    audit_retention_days: Option<u64>,
 }
 
 impl Default for LoggingConfig {
@@ -976,6 +1265,7 @@ impl Default for LoggingConfig {
             file: None,
             audit_enabled: true,
             audit_file: None,
+            audit_retention_days: None,
         }
     }
 }
@@ -1012,6 +1302,12 @@ struct ContainerRuntimeConfig {
     runtime: Option<container::RuntimeType>,
     /// Custom path to the container runtime binary
     binary: Option<String>,
+    /// Drive the runtime through its CLI ("cli") or its REST API socket ("socket")
+    api: container::RuntimeApi,
+    /// API socket in socket mode (auto-detected if not set)
+    socket: Option<String>,
+    /// Cluster of session pods when api = "kubernetes"
+    kubernetes: container::KubernetesConfig,
     /// Default container image for sessions
     default_image: String,
     /// Base port for allocating session ports
@@ -1020,6 +1316,8 @@ struct ContainerRuntimeConfig {
     user_data_path: Option<String>,
     /// Path to skeleton directory for new user homes
     skel_path: Option<String>,
+    /// Default resource limits of session containers
+    resources: container::ResourceLimits,
 }
 
 impl Default for ContainerRuntimeConfig {
@@ -1027,10 +1325,14 @@ impl Default for ContainerRuntimeConfig {
         Self {
             runtime: None,
             binary: None,
+            api: container::RuntimeApi::Cli,
+            socket: None,
+            kubernetes: container::KubernetesConfig::default(),
             default_image: "oqto:latest".to_string(),
             base_port: 41820,
             user_data_path: None,
             skel_path: None,
+            resources: container::ResourceLimits::default(),
         }
     }
 }
@@ -1094,6 +1396,8 @@ struct LinuxUsersConfig {
     /// When true, multi-user runtime paths must use persisted linux_username/linux_uid
     /// and may not fall back to legacy user_id-derived identities.
     strict_identity: bool,
+    /// Disk quota for each new user's home filesystem, in GiB (0 = none)
+    quota_gb: u64,
 }
 
 impl Default for LinuxUsersConfig {
@@ -1107,6 +1411,7 @@ impl Default for LinuxUsersConfig {
             use_sudo: true,
             create_home: true,
             strict_identity: false,
+            quota_gb: 0,
         }
     }
 }
@@ -1311,6 +1616,7 @@ fn resolve_local_runtime_wiring(
             use_sudo: local_cfg.linux_users.use_sudo,
             create_home: local_cfg.linux_users.create_home,
             strict_identity: local_cfg.linux_users.strict_identity,
+            quota_gb: local_cfg.linux_users.quota_gb,
         })
     } else {
         None
@@ -1412,6 +1718,7 @@ async fn handle_search(command: SearchCommand) -> Result<()> {
                     workspace_id: workspace_id_owned.as_deref(),
                     cwd: Some(&cwd),
                     limit: cmd.limit,
+                    filters: Default::default(),
                 },
             )
             .await?;
@@ -1421,6 +1728,42 @@ async fn handle_search(command: SearchCommand) -> Result<()> {
     Ok(())
 }
 
+/// Where feedback is archived: `private_archive` with the local storage
+/// backend, `feedback/` in the shared object store otherwise.
+fn feedback_archive(
+    ctx: &RuntimeContext,
+    object_storage: &Arc<dyn storage::StorageBackend>,
+) -> (Arc<dyn storage::StorageBackend>, &'static str) {
+    match ctx.config.storage.backend {
+        storage::StorageKind::Local => (
+            Arc::new(storage::LocalStorage::new(
+                ctx.config.feedback.private_archive.clone(),
+            )),
+            "",
+        ),
+        storage::StorageKind::S3 => (Arc::clone(object_storage), "feedback/"),
+    }
+}
+
+async fn handle_feedback(ctx: &RuntimeContext, command: FeedbackCommand) -> Result<()> {
+    match command {
+        FeedbackCommand::Reprocess => {
+            let object_storage = storage::from_config(&ctx.config.storage, &ctx.paths.data_dir)
+                .context("initializing [storage] backend")?;
+            let (archive, prefix) = feedback_archive(ctx, &object_storage);
+            let summary =
+                feedback::reprocess_feedback(&ctx.config.feedback, archive.as_ref(), prefix)
+                    .await?;
+            println!("{}", serde_json::to_string_pretty(&summary)?);
+        }
+        FeedbackCommand::Prune => {
+            let removed = feedback::prune_raw_feedback(&ctx.config.feedback).await?;
+            println!("Removed {removed} raw feedback item(s)");
+        }
+    }
+    Ok(())
+}
+
 fn handle_runner(ctx: &RuntimeContext, command: RunnerCommand) -> Result<()> {
     use std::process::Command as StdCommand;
 
@@ -1796,8 +2139,8 @@ async fn handle_invite_codes(ctx: &RuntimeContext, cmd: InviteCodesCommand) -> R
     if old_db_path.exists() && !db_path.exists() {
         std::fs::rename(&old_db_path, &db_path).ok();
     }
-    let database = db::Database::new(&db_path).await?;
-    let invite_repo = invite::InviteCodeRepository::new(database.pool().clone());
+    let database = db::Database::connect(&db_path, &ctx.config.db).await?;
+    let invite_repo = invite::InviteCodeRepository::new(database.shared().clone());
 
     match cmd {
         InviteCodesCommand::Generate(gen_cmd) => {
@@ -1895,12 +2238,115 @@ async fn handle_invite_codes(ctx: &RuntimeContext, cmd: InviteCodesCommand) -> R
             }
         }
         InviteCodesCommand::Revoke(revoke_cmd) => {
-            invite_repo.revoke(&revoke_cmd.code_id).await?;
+            if let Some(code_id) = &revoke_cmd.code_id {
+                invite_repo.revoke(code_id).await?;
+
+                if ctx.common.json {
+                    println!(r#"{{"status": "revoked", "id": "{}"}}"#, code_id);
+                } else {
+                    println!("Revoked invite code: {}", code_id);
+                }
+            } else {
+                let revoked = invite_repo
+                    .revoke_matching(revoke_cmd.note.as_deref(), revoke_cmd.prefix.as_deref())
+                    .await?;
+
+                if ctx.common.json {
+                    println!(r#"{{"status": "revoked", "count": {}}}"#, revoked);
+                } else {
+                    println!("Revoked {} invite code(s)", revoked);
+                }
+            }
+        }
+        InviteCodesCommand::Export(export_cmd) => {
+            let query = invite::InviteCodeExportQuery {
+                valid: match export_cmd.filter.as_str() {
+                    "valid" => Some(true),
+                    "invalid" => Some(false),
+                    _ => None,
+                },
+                ..Default::default()
+            };
+            let codes = invite_repo.list_for_export(&query).await?;
+
+            let output = match export_cmd.format.as_str() {
+                "csv" => invite::csv::export(&codes),
+                "json" => {
+                    let summaries: Vec<invite::InviteCodeSummary> =
+                        codes.iter().cloned().map(Into::into).collect();
+                    serde_json::to_string_pretty(&summaries)? + "\n"
+                }
+                other => return Err(anyhow!("unsupported format '{}', use csv or json", other)),
+            };
+            match &export_cmd.output {
+                Some(path) => {
+                    fs::write(path, output)
+                        .with_context(|| format!("writing {}", path.display()))?;
+                    eprintln!(
+                        "Exported {} invite code(s) to {}",
+                        codes.len(),
+                        path.display()
+                    );
+                }
+                None => print!("{}", output),
+            }
+        }
+        InviteCodesCommand::Import(import_cmd) => {
+            let csv = if import_cmd.file.as_os_str() == "-" {
+                io::read_to_string(io::stdin()).context("reading stdin")?
+            } else {
+                fs::read_to_string(&import_cmd.file)
+                    .with_context(|| format!("reading {}", import_cmd.file.display()))?
+            };
+            let result = invite_repo
+                .import(
+                    invite::ImportInviteCodesRequest {
+                        csv: Some(csv),
+                        max_uses: import_cmd.uses_per_code,
+                        note: import_cmd.note,
+                        ..Default::default()
+                    },
+                    &import_cmd.admin_id,
+                )
+                .await?;
 
             if ctx.common.json {
-                println!(r#"{{"status": "revoked", "id": "{}"}}"#, revoke_cmd.code_id);
+                println!("{}", serde_json::to_string_pretty(&result)?);
             } else {
-                println!("Revoked invite code: {}", revoke_cmd.code_id);
+                println!("Imported {} invite code(s)", result.imported.len());
+                if !result.duplicates.is_empty() {
+                    println!("Skipped {} duplicate(s):", result.duplicates.len());
+                    for duplicate in &result.duplicates {
+                        let reason = match duplicate.reason {
+                            invite::DuplicateReason::AlreadyExists => "already exists",
+                            invite::DuplicateReason::RepeatedInImport => "repeated in file",
+                        };
+                        println!("  {} ({})", duplicate.code, reason);
+                    }
+                }
+            }
+        }
+        InviteCodesCommand::History(history_cmd) => {
+            if invite_repo.get(&history_cmd.code_id).await?.is_none() {
+                return Err(anyhow!("invite code not found: {}", history_cmd.code_id));
+            }
+            let redemptions = invite_repo.list_redemptions(&history_cmd.code_id).await?;
+
+            if ctx.common.json {
+                println!("{}", serde_json::to_string_pretty(&redemptions)?);
+            } else {
+                println!("{:<20} {:<24} USER ID", "REDEEMED AT", "USERNAME");
+                println!("{}", "-".repeat(70));
+                for redemption in &redemptions {
+                    println!(
+                        "{:<20} {:<24} {}",
+                        redemption.redeemed_at,
+                        redemption.username,
+                        redemption.user_id.as_deref().unwrap_or("(deleted)")
+                    );
+                }
+                println!();
+                println!("Total: {} redemption(s)", redemptions.len());
             }
         }
     }
@@ -1990,7 +2436,11 @@ async fn handle_serve(ctx: &RuntimeContext, cmd: ServeCommand) -> Result<()> {
         );
     }
     info!("Database path: {}", db_path.display());
-    let database = db::Database::new(&db_path).await?;
+    let db_health = Arc::new(db::DbHealth::default());
+    db::prepare_database(&db_path, &ctx.config.db_integrity, &db_health)
+        .await
+        .context("checking database integrity")?;
+    let database = db::Database::connect(&db_path, &ctx.config.db).await?;
 
     // Initialize authentication from config
     let auth_config = ctx.config.auth.clone();
@@ -2008,7 +2458,7 @@ async fn handle_serve(ctx: &RuntimeContext, cmd: ServeCommand) -> Result<()> {
     let auth_state = auth::AuthState::new(auth_config);
 
     // Repositories that need to be available during early service construction.
-    let user_repo_for_services = user::UserRepository::new(database.pool().clone());
+    let user_repo_for_services = user::UserRepository::new(database.shared().clone());
 
     // Determine runtime mode: CLI --local-mode overrides config
     let runtime_mode = match ctx.config.backend.mode {
@@ -2036,31 +2486,79 @@ async fn handle_serve(ctx: &RuntimeContext, cmd: ServeCommand) -> Result<()> {
     );
 
     // Initialize runtimes based on mode
-    let container_runtime: Option<std::sync::Arc<container::ContainerRuntime>> = if !local_mode {
-        let runtime = match (&ctx.config.container.runtime, &ctx.config.container.binary) {
-            (Some(rt), Some(binary)) => {
-                container::ContainerRuntime::with_binary(*rt, binary.clone())
-            }
-            (Some(rt), None) => container::ContainerRuntime::with_type(*rt),
-            (None, _) => container::ContainerRuntime::new(),
-        };
-
-        // Check container runtime is available
-        match runtime.health_check().await {
-            Ok(_) => info!(
-                "Container runtime ({}) is available",
-                runtime.runtime_type()
-            ),
-            Err(e) => log::warn!(
-                "Container runtime health check failed: {:?}. Container operations may fail.",
-                e
-            ),
-        }
+    let container_runtime: Option<std::sync::Arc<dyn container::ContainerRuntimeApi>> =
+        if !local_mode {
+            let config = &ctx.config.container;
+            let runtime: std::sync::Arc<dyn container::ContainerRuntimeApi> = match config.api {
+                container::RuntimeApi::Cli => {
+                    let runtime = match (&config.runtime, &config.binary) {
+                        (Some(rt), Some(binary)) => {
+                            container::ContainerRuntime::with_binary(*rt, binary.clone())
+                        }
+                        (Some(rt), None) => container::ContainerRuntime::with_type(*rt),
+                        (None, _) => container::ContainerRuntime::new(),
+                    };
 
-        Some(std::sync::Arc::new(runtime))
-    } else {
-        None
-    };
+                    // Check container runtime is available
+                    match runtime.health_check().await {
+                        Ok(_) => info!(
+                            "Container runtime ({}) is available",
+                            runtime.runtime_type()
+                        ),
+                        Err(e) => log::warn!(
+                            "Container runtime health check failed: {:?}. Container operations may fail.",
+                            e
+                        ),
+                    }
+                    std::sync::Arc::new(runtime)
+                }
+                container::RuntimeApi::Socket => {
+                    let socket = config
+                        .socket
+                        .as_deref()
+                        .map(PathBuf::from)
+                        .or_else(container::default_socket_path)
+                        .context(
+                            "no container API socket found; set container.socket \
+                             (e.g. $XDG_RUNTIME_DIR/podman/podman.sock)",
+                        )?;
+                    let runtime = container::SocketRuntime::new(socket, config.runtime);
+
+                    match runtime.health_check().await {
+                        Ok(_) => info!(
+                            "Container runtime API ({}) is available at {}",
+                            runtime.runtime_type(),
+                            runtime.socket().display()
+                        ),
+                        Err(e) => log::warn!(
+                            "Container runtime API health check failed: {:?}. Container operations may fail.",
+                            e
+                        ),
+                    }
+                    std::sync::Arc::new(runtime)
+                }
+                container::RuntimeApi::Kubernetes => {
+                    let runtime = container::KubernetesRuntime::new(config.kubernetes.clone())
+                        .context("invalid [container.kubernetes] configuration")?;
+
+                    match runtime.health_check().await {
+                        Ok(_) => info!(
+                            "Kubernetes API at {} is available (namespace {})",
+                            runtime.api_server(),
+                            runtime.namespace()
+                        ),
+                        Err(e) => log::warn!(
+                            "Kubernetes API health check failed: {:?}. Container operations may fail.",
+                            e
+                        ),
+                    }
+                    std::sync::Arc::new(runtime)
+                }
+            };
+            Some(runtime)
+        } else {
+            None
+        };
 
     let local_runtime: Option<local::LocalRuntime> = if local_mode {
         // Build Linux users config
@@ -2073,6 +2571,7 @@ async fn handle_serve(ctx: &RuntimeContext, cmd: ServeCommand) -> Result<()> {
             use_sudo: ctx.config.local.linux_users.use_sudo,
             create_home: ctx.config.local.linux_users.create_home,
             strict_identity: ctx.config.local.linux_users.strict_identity,
+            quota_gb: ctx.config.local.linux_users.quota_gb,
         };
 
         // Load sandbox config from separate file (~/.config/oqto/sandbox.toml)
@@ -2225,12 +2724,32 @@ async fn handle_serve(ctx: &RuntimeContext, cmd: ServeCommand) -> Result<()> {
     };
 
     let resolved_runner_socket_pattern = resolve_runner_socket_pattern(&ctx.config);
+    if ctx.config.backend.runner.transport == oqto_runner::client::Transport::Grpc {
+        oqto_runner::client::use_grpc_transport(ctx.config.backend.runner.grpc.clone());
+        info!(
+            tls = ctx.config.backend.runner.grpc.enabled(),
+            "Using gRPC transport for runner communication"
+        );
+    }
 
+    // Volumes whose headroom the admin overview reports.
+    let mut overview_volumes = vec![
+        ("data".to_string(), ctx.paths.data_dir.clone()),
+        ("user_data".to_string(), user_data_path.clone()),
+    ];
+    overview_volumes.dedup_by(|a, b| a.1 == b.1);
+
+    ctx.config
+        .container
+        .resources
+        .validate()
+        .context("Invalid [container.resources] configuration")?;
     let session_config = session::SessionServiceConfig {
         default_image,
         base_port,
         user_data_path,
         skel_path,
+        resource_limits: ctx.config.container.resources.clone(),
         default_session_budget_usd: ctx
             .config
             .eavs
@@ -2245,6 +2764,7 @@ async fn handle_serve(ctx: &RuntimeContext, cmd: ServeCommand) -> Result<()> {
         mmry_container_url: ctx.config.mmry.container_url.clone(),
         max_concurrent_sessions: ctx.config.sessions.max_concurrent_sessions,
         idle_timeout_minutes: ctx.config.sessions.idle_timeout_minutes,
+        idle_action: ctx.config.sessions.idle_action,
         idle_check_interval_seconds: ctx.config.sessions.idle_check_interval_seconds,
         // Enable pi-bridge in containers when Pi is enabled and runtime mode is container
         pi_bridge_enabled: ctx.config.pi.enabled
@@ -2260,7 +2780,7 @@ async fn handle_serve(ctx: &RuntimeContext, cmd: ServeCommand) -> Result<()> {
         },
     };
 
-    let session_repo = session::SessionRepository::new(database.pool().clone());
+    let session_repo = session::SessionRepository::new(database.shared().clone());
 
     // Initialize EAVS client if configured
     let eavs_client: Option<std::sync::Arc<dyn eavs::EavsApi>> = if let Some(ref eavs_config) =
@@ -2364,6 +2884,78 @@ async fn handle_serve(ctx: &RuntimeContext, cmd: ServeCommand) -> Result<()> {
         }
     };
 
+    // Enforce the egress rules of sandbox.toml and the sessions' sandbox
+    // profiles on session containers. A sandbox.toml that cannot be loaded
+    // fails every container start rather than running without rules.
+    if !local_mode {
+        let egress = match oqto_sandbox::SandboxConfig::load_global() {
+            Ok(sandbox) => session::ContainerEgress::new(sandbox),
+            Err(e) => {
+                error!("Failed to load sandbox config, container sessions will not start: {e:#}");
+                session::ContainerEgress::unavailable(&e)
+            }
+        };
+        session_service = session_service.with_egress(egress);
+    }
+
+    let secret_store = if ctx.config.secrets.enabled {
+        // Replicas sharing a database must seal with the same key, so it is
+        // only generated for a local one.
+        let shared_db = !ctx.config.db.url.is_empty();
+        let (sealer, key_origin) = match (&ctx.config.secrets.key, &ctx.config.secrets.key_file) {
+            (Some(key), _) => {
+                let key = auth::resolve_secret(key).context("resolving [secrets] key")?;
+                let sealer = secrets::Sealer::from_hex(&key).context("invalid [secrets] key")?;
+                (sealer, "[secrets] key".to_string())
+            }
+            (None, Some(path)) => {
+                let path = expand_path(path.clone())?;
+                let sealer = if shared_db {
+                    secrets::Sealer::load(&path)
+                } else {
+                    secrets::Sealer::load_or_create(&path)
+                }
+                .context("loading the [secrets] key")?;
+                (sealer, path.display().to_string())
+            }
+            (None, None) if shared_db => anyhow::bail!(
+                "[secrets] needs key_file or key when [db] url is set: every replica must \
+                 seal secrets with the same key"
+            ),
+            (None, None) => {
+                let path = ctx.paths.data_dir.join("secrets.key");
+                let sealer =
+                    secrets::Sealer::load_or_create(&path).context("loading the [secrets] key")?;
+                (sealer, path.display().to_string())
+            }
+        };
+        let store = Arc::new(secrets::SecretStore::new(
+            secrets::SecretRepository::new(database.shared().clone()),
+            sealer,
+            ctx.paths.data_dir.join("session-secrets"),
+        ));
+        session_service = session_service.with_secrets(store.clone());
+        info!("Secrets enabled (key: {key_origin})");
+        Some(store)
+    } else {
+        None
+    };
+
+    let registration_service = if ctx.config.registration.open {
+        let service = Arc::new(
+            registration::RegistrationService::new(
+                registration::RegistrationRepository::new(database.shared().clone()),
+                ctx.config.registration.clone(),
+            )
+            .context("invalid [registration] configuration")?,
+        );
+        session_service = session_service.with_registration(service.clone());
+        info!("Open registration enabled");
+        Some(service)
+    } else {
+        None
+    };
+
     let mut sldr_users: Option<local::UserSldrManager> = None;
 
     // Enable per-user mmry instances in local multi-user mode.
@@ -2424,21 +3016,20 @@ async fn handle_serve(ctx: &RuntimeContext, cmd: ServeCommand) -> Result<()> {
         let browser_mgr = agent_browser::AgentBrowserManager::new(ctx.config.agent_browser.clone());
         browser_mgr.cleanup_all_sessions();
     }
+    session_service.start_agent_browser_pool();
 
     // Start idle session cleanup background task
     // Check every 5 minutes, stop sessions idle for 30 minutes
     let session_service_arc = std::sync::Arc::new(session_service.clone());
-    let _idle_cleanup_handle = session_service_arc.start_idle_session_cleanup_task(
-        session_config.idle_check_interval_seconds,
-        session_config.idle_timeout_minutes,
-    );
+    let _idle_cleanup_handle = session_service_arc
+        .start_idle_session_cleanup_task(session_config.idle_check_interval_seconds);
 
     // Initialize user service
-    let user_repo = user::UserRepository::new(database.pool().clone());
+    let user_repo = user::UserRepository::new(database.shared().clone());
     let user_service = user::UserService::new(user_repo);
 
     // Initialize invite code repository
-    let invite_repo = invite::InviteCodeRepository::new(database.pool().clone());
+    let invite_repo = invite::InviteCodeRepository::new(database.shared().clone());
 
     // Clone session_service before creating state for shutdown handler
     let session_service_for_shutdown = session_service.clone();
@@ -2456,36 +3047,7 @@ async fn handle_serve(ctx: &RuntimeContext, cmd: ServeCommand) -> Result<()> {
         user_port_range: ctx.config.mmry.user_port_range,
     };
 
-    // Build voice state based on configuration
-    let voice_state = api::VoiceState {
-        enabled: ctx.config.voice.enabled,
-        stt_url: ctx.config.voice.stt_url.clone(),
-        tts_url: ctx.config.voice.tts_url.clone(),
-        vad_timeout_ms: ctx.config.voice.vad_timeout_ms,
-        default_voice: ctx.config.voice.default_voice.clone(),
-        default_speed: ctx.config.voice.default_speed,
-        auto_language_detect: ctx.config.voice.auto_language_detect,
-        tts_muted: ctx.config.voice.tts_muted,
-        continuous_mode: ctx.config.voice.continuous_mode,
-        default_visualizer: ctx.config.voice.default_visualizer.clone(),
-        interrupt_word_count: ctx.config.voice.interrupt_word_count,
-        interrupt_backoff_ms: ctx.config.voice.interrupt_backoff_ms,
-        visualizer_voices: ctx
-            .config
-            .voice
-            .visualizer_voices
-            .iter()
-            .map(|(k, v)| {
-                (
-                    k.clone(),
-                    api::VisualizerVoiceState {
-                        voice: v.voice.clone(),
-                        speed: v.speed,
-                    },
-                )
-            })
-            .collect(),
-    };
+    let voice_state = voice_state(&ctx.config.voice);
 
     let session_ui_state = api::SessionUiState {
         auto_attach: ctx.config.sessions.auto_attach,
@@ -2513,6 +3075,12 @@ async fn handle_serve(ctx: &RuntimeContext, cmd: ServeCommand) -> Result<()> {
     let oqto_config_dir = default_config_dir()?;
     let settings_oqto = settings::SettingsService::new(oqto_schema, oqto_config_dir, "config.toml")
         .context("Failed to create oqto settings service")?;
+    let settings_oqto = match &ctx.remote_config {
+        Some(source) => settings_oqto
+            .with_overlay(source.cache_path())
+            .context("Failed to layer remote config over oqto settings")?,
+        None => settings_oqto,
+    };
 
     // Create mmry settings service if mmry is enabled
     let settings_mmry = if ctx.config.mmry.enabled {
@@ -2562,7 +3130,7 @@ async fn handle_serve(ctx: &RuntimeContext, cmd: ServeCommand) -> Result<()> {
     let settings_pi_models =
         settings::SettingsService::new_json(pi_models_schema, pi_config_dir, "models.json").ok();
 
-    let api_key_repo = api_keys::ApiKeyRepository::new(database.pool().clone());
+    let api_key_repo = api_keys::ApiKeyRepository::new(database.shared().clone());
     let session_target_repo = session_target::SessionTargetRepository::new(database.pool().clone());
 
     // Create app state
@@ -2579,35 +3147,153 @@ async fn handle_serve(ctx: &RuntimeContext, cmd: ServeCommand) -> Result<()> {
         session_target_repo,
         max_proxy_body_bytes,
     );
-    state = state.with_feedback_config(ctx.config.feedback.clone());
+    state = state
+        .with_feedback_config(ctx.config.feedback.clone())
+        .with_dev_proxy_config(ctx.config.dev_proxy.clone())
+        .with_user_env_config(ctx.config.user_env.clone())
+        .with_workspace_encryption_config(ctx.config.workspace_encryption.clone())
+        .with_db_health(db_health)
+        .with_bookmarks(Arc::new(bookmarks::BookmarkRepository::new(
+            database.pool().clone(),
+        )))
+        .with_annotations(Arc::new(message_annotations::AnnotationRepository::new(
+            database.shared().clone(),
+        )))
+        .with_private_messages(Arc::new(private_messages::PrivateMessageRepository::new(
+            database.shared().clone(),
+        )))
+        .with_template_tests(Arc::new(template_tests::TemplateTestRepository::new(
+            database.shared().clone(),
+        )))
+        .with_session_shares(Arc::new(session_shares::SessionShareRepository::new(
+            database.shared().clone(),
+        )))
+        .with_session_drafts(Arc::new(session_drafts::SessionDraftRepository::new(
+            database.shared().clone(),
+        )))
+        .with_prompt_drafts(Arc::new(prompt_drafts::PromptDraftService::new(
+            prompt_drafts::PromptDraftRepository::new(database.shared().clone()),
+        )))
+        .with_git_credentials(Arc::new(git_credentials::GitCredentialRepository::new(
+            database.shared().clone(),
+        )));
+
+    let object_storage = storage::from_config(&ctx.config.storage, &ctx.paths.data_dir)
+        .context("initializing [storage] backend")?;
+    info!("Object storage: {}", object_storage.describe());
 
     if let Err(err) = feedback::ensure_feedback_dirs(&ctx.config.feedback) {
         warn!("Failed to initialize feedback directories: {}", err);
     } else {
         let feedback_config = ctx.config.feedback.clone();
+        // The local backend keeps archiving to `private_archive`; a shared
+        // backend collects feedback from all nodes under `feedback/`.
+        let (archive, prefix) = feedback_archive(ctx, &object_storage);
         tokio::spawn(async move {
-            feedback::sync_feedback_loop(feedback_config).await;
+            feedback::sync_feedback_loop(feedback_config, archive, prefix).await;
         });
     }
 
+    if ctx.config.tool_usage.enabled {
+        let tool_usage_service = Arc::new(tool_usage::ToolUsageService::new(
+            tool_usage::ToolUsageRepository::new(database.pool().clone()),
+            ctx.config.tool_usage.clone(),
+            state.ws_hub.clone(),
+        ));
+        tool_usage_service.start_maintenance_task();
+        state = state.with_tool_usage(tool_usage_service);
+        info!("Tool usage statistics enabled");
+    }
+
+    if ctx.config.usage.enabled {
+        let usage_service = Arc::new(eavs::UsageService::new(
+            eavs::UsageRepository::new(database.pool().clone()),
+            ctx.config.usage.clone(),
+        ));
+        usage_service.start_maintenance_task();
+        state = state.with_usage(usage_service);
+        info!("Spend attribution enabled");
+    }
+
+    if ctx.config.budget.enabled {
+        if ctx.config.usage.enabled {
+            state = state.with_budget(Arc::new(eavs::BudgetService::new(
+                eavs::BudgetRepository::new(database.pool().clone()),
+                ctx.config.budget.clone(),
+            )));
+            info!("Session budgets enabled");
+        } else {
+            warn!("[budget] needs [usage] enabled; session budgets are not enforced");
+        }
+    }
+
     // Add settings services to state
     state = state.with_settings_oqto(settings_oqto);
+
+    // Apply config.toml edits to running services.
+    let common = ctx.common.clone();
+    let log_level_pinned = ctx.log_level_pinned();
+    let config_reloader = Arc::new(config_reload::ConfigReloader::new(
+        loaded_config(&ctx.config)?,
+        Box::new(move || loaded_config(&RuntimeContext::new(common.clone())?.config)),
+        Box::new(move |level| {
+            if log_level_pinned {
+                anyhow::bail!("the log level is set on the command line or by RUST_LOG");
+            }
+            set_log_level(level)
+        }),
+        state.sessions.clone(),
+        state.voice.clone(),
+        state.settings_oqto.clone(),
+    ));
+    if ctx.config.config.watch {
+        match config_reloader.clone().watch(&ctx.paths.config_file) {
+            Ok(()) => info!("Watching {} for changes", ctx.paths.config_file.display()),
+            Err(e) => warn!("Config file watching disabled: {e:#}"),
+        }
+    }
+
+    // Refresh the remote config bundle; live settings are applied in place,
+    // other changes restart the server.
+    let config_restart = Arc::new(tokio::sync::Notify::new());
+    if let Some(source) = ctx.remote_config.clone() {
+        remote_config::RemoteConfigWatcher::new(
+            source,
+            ctx.config.config.clone(),
+            config_reloader.clone(),
+            config_restart.clone(),
+        )
+        .spawn();
+        info!("Remote config refresh enabled");
+    }
+    state = state.with_config_reloader(config_reloader);
     if let Some(mmry_settings) = settings_mmry {
         state = state.with_settings_mmry(mmry_settings);
     }
+    if let (Some(pi_settings), Some(pi_models)) = (&settings_pi_agent, &settings_pi_models) {
+        state = state.with_settings_sync(settings::SettingsSync::new(
+            Arc::clone(&state.sessions),
+            pi_settings.config_path(),
+            pi_models.config_path(),
+        ));
+    }
+    state = state.with_rbac(auth::RbacService::new(auth::RbacRepository::new(
+        database.shared().clone(),
+    )));
     if let Some(pi_settings) = settings_pi_agent {
         state = state.with_settings_pi_agent(pi_settings);
     }
     if let Some(pi_models) = settings_pi_models {
         state = state.with_settings_pi_models(pi_models);
     }
+    state = state.with_settings_history(settings::SettingsHistory::new(database.pool().clone()));
 
     if let Some(manager) = sldr_users {
         state = state.with_sldr_users(manager);
     }
 
     // Add onboarding service
-    let onboarding_service = onboarding::OnboardingService::new(database.pool().clone());
+    let onboarding_service = onboarding::OnboardingService::new(database.shared().clone());
     state = state.with_onboarding(onboarding_service);
     info!("Onboarding service initialized");
 
@@ -2622,7 +3308,7 @@ async fn handle_serve(ctx: &RuntimeContext, cmd: ServeCommand) -> Result<()> {
     }
 
     // Initialize shared workspaces service
-    let sw_repo = shared_workspace::SharedWorkspaceRepository::new(database.pool().clone());
+    let sw_repo = shared_workspace::SharedWorkspaceRepository::new(database.shared().clone());
     let mut sw_service =
         shared_workspace::SharedWorkspaceService::new(sw_repo).with_ws_hub(state.ws_hub.clone());
     if let Some(config) = linux_users_config {
@@ -2655,6 +3341,16 @@ async fn handle_serve(ctx: &RuntimeContext, cmd: ServeCommand) -> Result<()> {
     info!("Onboarding templates service initialized");
     state = state.with_onboarding_templates(onboarding_templates_service);
 
+    if let Some(script) = ctx
+        .config
+        .bootstrap_script
+        .load()
+        .context("invalid [bootstrap_script] configuration")?
+    {
+        info!("First-workspace bootstrap script enabled");
+        state = state.with_bootstrap_script(script);
+    }
+
     if ctx.config.logging.audit_enabled {
         let audit_path = ctx
             .config
@@ -2667,9 +3363,23 @@ async fn handle_serve(ctx: &RuntimeContext, cmd: ServeCommand) -> Result<()> {
             .unwrap_or_else(|| ctx.paths.state_dir.join("audit.log.jsonl"));
 
         match audit::AuditLogger::new(audit_path).await {
-            Ok(logger) => {
+            Ok(mut logger) => {
                 info!("Audit logging enabled at {}", logger.path().display());
-                state = state.with_audit_logger(Arc::new(logger));
+                if ctx.config.siem.enabled {
+                    let exporter = Arc::new(
+                        siem::SiemExporter::start(ctx.config.siem.clone())
+                            .context("invalid [siem] configuration")?,
+                    );
+                    info!("SIEM export enabled ({})", ctx.config.siem.transport);
+                    logger = logger.with_exporter(Arc::clone(&exporter));
+                    state = state.with_siem(exporter);
+                }
+                let logger = Arc::new(logger);
+                if let Some(days) = ctx.config.logging.audit_retention_days.filter(|d| *d > 0) {
+                    info!("Audit events are kept for {} days", days);
+                    logger.start_retention_task(days);
+                }
+                state = state.with_audit_logger(logger);
             }
             Err(err) => {
                 warn!("Failed to initialize audit logger: {}", err);
@@ -2677,6 +3387,9 @@ async fn handle_serve(ctx: &RuntimeContext, cmd: ServeCommand) -> Result<()> {
         }
     } else {
         info!("Audit logging disabled");
+        if ctx.config.siem.enabled {
+            warn!("SIEM export requires logging.audit_enabled; not exporting");
+        }
     }
 
     // Initialize hstry (chat history) service
@@ -2807,12 +3520,351 @@ async fn handle_serve(ctx: &RuntimeContext, cmd: ServeCommand) -> Result<()> {
         warn!("Session target backfill failed: {}", err);
     }
 
+    if ctx.config.db_integrity.enabled {
+        db::start_integrity_monitor(
+            state.clone(),
+            database.pool().clone(),
+            db_path.clone(),
+            ctx.config.db_integrity.clone(),
+        );
+    }
+
+    if ctx.config.status_page.enabled {
+        let status_service = Arc::new(status::StatusService::new(
+            status::StatusRepository::new(database.pool().clone()),
+            ctx.config.status_page.clone(),
+            state.db_health.clone(),
+            state.eavs_client.clone(),
+        ));
+        status_service.start_probe_task();
+        state = state.with_status(status_service);
+    }
+
+    // Delegated workspace access is enforced with ACLs for the grantee's
+    // Linux account, so it needs per-user isolation.
+    if ctx.config.workspace_access.enabled
+        && let Some(linux_users) = state.linux_users.clone().filter(|lu| lu.enabled)
+    {
+        let access_service = Arc::new(workspace_access::WorkspaceAccessService::new(
+            workspace_access::WorkspaceAccessRepository::new(database.pool().clone()),
+            ctx.config.workspace_access.clone(),
+            linux_users,
+            state.ws_hub.clone(),
+        ));
+        access_service.start_sweep_task();
+        state = state.with_workspace_access(access_service);
+        info!("Delegated workspace access enabled");
+    }
+
+    state = state.with_crash_bundles(Arc::new(
+        crash_bundles::CrashBundleService::new(crash_bundles::CrashBundleRepository::new(
+            database.pool().clone(),
+        ))
+        .with_storage(Arc::clone(&object_storage)),
+    ));
+
+    if ctx.config.workspace_backups.enabled {
+        state =
+            state.with_workspace_backups(Arc::new(workspace_backups::WorkspaceBackupService::new(
+                ctx.config.workspace_backups.clone(),
+                Arc::clone(&object_storage),
+            )));
+        info!(
+            "Workspace backups enabled (every {}h)",
+            ctx.config.workspace_backups.interval_hours
+        );
+    }
+
+    if ctx.config.auth.oidc.enabled {
+        // Dev mode may run without a JWT secret; sign-ins then only
+        // complete on this process.
+        let secret = ctx
+            .config
+            .auth
+            .resolve_jwt_secret()?
+            .unwrap_or_else(auth::AuthConfig::generate_jwt_secret);
+        let provider = auth::OidcProvider::new(ctx.config.auth.oidc.clone(), &secret)
+            .context("invalid [auth.oidc] configuration")?;
+        info!("Single sign-on enabled ({})", ctx.config.auth.oidc.issuer);
+        state = state.with_oidc(Arc::new(provider));
+    }
+
+    if ctx.config.auth.passkeys.enabled {
+        let passkeys =
+            auth::PasskeyService::new(&ctx.config.auth.passkeys, database.shared().clone())
+                .context("invalid [auth.passkeys] configuration")?;
+        info!(
+            "Passkey sign-in enabled ({})",
+            ctx.config.auth.passkeys.rp_id
+        );
+        state = state.with_passkeys(Arc::new(passkeys));
+    }
+
+    // Impersonated actions must be traceable, so impersonation needs the
+    // audit log.
+    if ctx.config.impersonation.enabled {
+        if state.audit_logger.is_none() {
+            warn!("Impersonation is enabled but audit logging is not; impersonation stays off");
+        } else {
+            let service = impersonation::ImpersonationService::new(
+                impersonation::ImpersonationRepository::new(database.shared().clone()),
+                ctx.config.impersonation.clone(),
+            )
+            .context("invalid [impersonation] configuration")?;
+            state = state.with_impersonations(Arc::new(service));
+            info!(
+                "Impersonation enabled (break glass {})",
+                if ctx.config.impersonation.allow_break_glass {
+                    "allowed"
+                } else {
+                    "disabled"
+                }
+            );
+        }
+    }
+
+    if ctx.config.resource_telemetry.enabled {
+        state = state.with_session_resources(Arc::new(session::SessionResourceMonitor::new(
+            ctx.config.resource_telemetry.clone(),
+        )));
+    }
+
+    if ctx.config.long_poll.enabled {
+        state = state.with_session_events(Arc::new(session_events::SessionEventFeeds::new(
+            ctx.config.long_poll.clone(),
+        )));
+    }
+
+    if !ctx.config.runners.is_empty() {
+        let pool = Arc::new(
+            runner::federation::RunnerPool::new(
+                ctx.config.runners.clone(),
+                ctx.config.runner_federation.clone(),
+                runner::placement::RunnerPlacementRepository::new(database.shared().clone()),
+            )
+            .context("invalid [[runners]] configuration")?,
+        );
+        pool.start_health_task();
+        info!(
+            "Scheduling sessions across {} runner hosts",
+            ctx.config.runners.len()
+        );
+        state = state.with_runner_pool(pool);
+    }
+
+    // Before the scheduler, whose catch-up runs take background slots.
+    if ctx.config.priority_lanes.enabled {
+        state = state.with_priority_lanes(Arc::new(priority_lanes::PriorityLanes::new(
+            ctx.config.priority_lanes.clone(),
+        )));
+    }
+
+    if ctx.config.scheduler.enabled {
+        let scheduler_service = Arc::new(scheduler::SchedulerService::new(
+            scheduler::ScheduleRunRepository::new(database.shared().clone()),
+            scheduler::AgentScheduleRepository::new(database.shared().clone()),
+            ctx.config.scheduler.clone(),
+            state.ws_hub.clone(),
+        ));
+        // Account for downtime before the heartbeat overwrites its start.
+        let missed = scheduler_service
+            .account_downtime(chrono::Utc::now())
+            .await
+            .unwrap_or_else(|e| {
+                warn!("Failed to account for missed scheduled runs: {:#}", e);
+                Vec::new()
+            });
+        scheduler_service.start_heartbeat_task();
+        state = state.with_scheduler(scheduler_service);
+        if ctx.config.scheduler.catch_up == scheduler::CatchUpPolicy::RunOnce && !missed.is_empty()
+        {
+            tokio::spawn(api::handlers::scheduler_catch_up(state.clone(), missed));
+        }
+        tokio::spawn(api::handlers::run_agent_schedules(state.clone()));
+    }
+
+    if ctx.config.memory_promotion.enabled {
+        let promotion_service = Arc::new(memory_promotion::MemoryPromotionService::new(
+            memory_promotion::MemorySuggestionRepository::new(database.pool().clone()),
+            ctx.config.memory_promotion.clone(),
+            state.ws_hub.clone(),
+        ));
+        promotion_service.start_sweep_task();
+        state = state.with_memory_promotion(promotion_service);
+    }
+
+    if ctx.config.vulnerability_scan.enabled {
+        let repo = vuln_scan::VulnScanRepository::new(database.pool().clone());
+        match repo.fail_interrupted().await {
+            Ok(0) => {}
+            Ok(n) => info!("Marked {} interrupted vulnerability scans as failed", n),
+            Err(e) => warn!("Failed to reset interrupted vulnerability scans: {:#}", e),
+        }
+        let vuln_service = Arc::new(vuln_scan::VulnScanService::new(
+            repo,
+            ctx.config.vulnerability_scan.clone(),
+            state.ws_hub.clone(),
+        ));
+        state = state.with_vuln_scans(vuln_service);
+    }
+
+    if ctx.config.outbox.enabled {
+        let outbox_service = Arc::new(
+            outbox::OutboxService::new(
+                outbox::OutboxRepository::new(database.pool().clone()),
+                ctx.config.outbox.clone(),
+                state.ws_hub.clone(),
+                state.audit_logger.clone(),
+            )
+            .context("invalid [outbox] configuration")?,
+        );
+        if state.audit_logger.is_none() {
+            warn!("Outbox enabled without audit logging; sends are not audited");
+        }
+        outbox_service.start_sweep_task();
+        state = state.with_outbox(outbox_service);
+    }
+
+    if ctx.config.triggers.enabled {
+        let repo = triggers::TriggerRepository::new(database.shared().clone());
+        match repo.fail_interrupted().await {
+            Ok(0) => {}
+            Ok(n) => info!("Marked {} interrupted trigger runs as failed", n),
+            Err(e) => warn!("Failed to reset interrupted trigger runs: {:#}", e),
+        }
+        let trigger_service = Arc::new(
+            triggers::TriggerService::new(repo, ctx.config.triggers.clone())
+                .context("invalid [triggers] configuration")?,
+        );
+        state = state.with_triggers(trigger_service);
+        if ctx.config.triggers.imap.enabled {
+            tokio::spawn(api::handlers::run_trigger_mail_poller(state.clone()));
+        }
+    }
+
+    if !ctx.config.webhooks.is_empty() {
+        let webhook_service = Arc::new(
+            webhooks::WebhookService::new(
+                ctx.config.webhooks.clone(),
+                webhooks::WebhookDeliveryRepository::new(database.shared().clone()),
+            )
+            .context("invalid [[webhooks]] configuration")?,
+        );
+        info!("Webhooks enabled ({} endpoints)", ctx.config.webhooks.len());
+        tokio::spawn(Arc::clone(&webhook_service).run());
+        state = state.with_webhooks(webhook_service);
+    }
+
+    if let Some(service) = registration_service {
+        state = state.with_registration(service);
+    }
+
+    if let Some(store) = secret_store {
+        state = state.with_secrets(store);
+    }
+
+    if ctx.config.session_tags.enabled {
+        if !ctx.config.session_tags.model.is_empty() && state.eavs_client.is_none() {
+            warn!("[session_tags] model is set but EAVS is not configured; using keyword rules");
+        }
+        state = state.with_session_tags(Arc::new(session_tags::SessionTagService::new(
+            session_tags::SessionTagRepository::new(database.pool().clone()),
+            ctx.config.session_tags.clone(),
+            state.eavs_client.clone(),
+        )));
+    }
+
+    if ctx.config.macros.enabled {
+        state = state.with_macros(Arc::new(macros::MacroService::new(
+            macros::MacroRepository::new(database.pool().clone()),
+            ctx.config.macros.clone(),
+        )));
+    }
+
+    if ctx.config.metrics.enabled {
+        if ctx.config.metrics.bearer_token.is_empty() {
+            warn!("[metrics] bearer_token is not set; /api/metrics is not served");
+        }
+        observability::metrics::install_observers();
+        state = state.with_metrics(Arc::new(observability::metrics::MetricsService::new(
+            ctx.config.metrics.clone(),
+            state.eavs_client.clone(),
+        )));
+    }
+
+    if ctx.config.rate_limit.enabled {
+        state = state.with_rate_limiter(Arc::new(api::RateLimiter::new(
+            ctx.config.rate_limit.clone(),
+        )));
+    }
+
+    let capability_registry = Arc::new(capabilities::CapabilityRegistry::default());
+    let probe_targets = register_capabilities(
+        &ctx.config,
+        &capability_registry,
+        local_mode && !single_user,
+        state.eavs_client.is_some(),
+        state.sldr_users.is_some(),
+    );
+    for notice in state.db_health.notices() {
+        if notice.component == "hstry" {
+            capability_registry.degrade(capabilities::Subsystem::Hstry, notice.message);
+        }
+    }
+    capability_registry.start_probe_task(probe_targets);
+    let voice_health = Arc::new(voice_health::VoiceHealthMonitor::new(
+        state.voice.clone(),
+        capability_registry.clone(),
+        state.ws_hub.clone(),
+        ctx.config.voice.health_failure_threshold,
+    ));
+    voice_health.start(Duration::from_secs(
+        ctx.config.voice.health_check_interval_secs.max(1),
+    ));
+    state = state
+        .with_capabilities(capability_registry)
+        .with_voice_health(voice_health);
+
+    state = state.with_admin_overview(Arc::new(admin_overview::AdminOverviewService::new(
+        overview_volumes,
+        state.eavs_client.clone(),
+    )));
+    state = state.with_project_cards(Arc::new(project_cards::ProjectCardService::new()));
+
+    if ctx.config.event_replay.enabled {
+        state = state.with_event_streams(Arc::new(ws::SessionStreams::new(
+            ctx.config.event_replay.clone(),
+        )));
+    }
+    let reconnect = Arc::new(ws::ReconnectGovernor::new(ctx.config.reconnect.clone()));
+    state = state.with_reconnect(Arc::clone(&reconnect));
+
+    if state.budget.is_some() {
+        tokio::spawn(api::handlers::run_budget_checks(state.clone()));
+    }
+    tokio::spawn(api::proxy::run_preview_port_watch(state.clone()));
+    tokio::spawn(api::handlers::run_resource_telemetry(state.clone()));
+    tokio::spawn(api::handlers::run_port_conflict_events(state.clone()));
+    tokio::spawn(api::handlers::run_blocked_connection_events(state.clone()));
+    if state.webhooks.is_some() {
+        tokio::spawn(api::handlers::run_session_webhooks(state.clone()));
+    }
+    tokio::spawn(api::handlers::run_scheduled_session_drafts(state.clone()));
+    if state.workspace_backups.is_some() {
+        tokio::spawn(api::handlers::run_workspace_backups(state.clone()));
+    }
+
     // Create router - all API routes are served under /api prefix only.
     // This is the single source of truth for routing. All clients (frontend,
     // internal services, containers) must use /api/* paths.
     let admin_state = state.clone();
     let api_router = api::create_router_with_config(state, ctx.config.server.max_upload_size_mb);
-    let app = with_frontend_static_if_available(api_router);
+    let app = with_frontend_static_if_available(api_router)
+        .layer(axum::middleware::from_fn_with_state(
+            net::TrustedProxies::new(ctx.config.server.trusted_proxies.iter().copied()),
+            net::resolve_client_ip,
+        ))
+        .layer(axum::middleware::from_fn(net::canonical_peer));
 
     #[cfg(unix)]
     if let Some(ref socket_path) = ctx.config.server.admin_socket_path {
@@ -2834,13 +3886,13 @@ async fn handle_serve(ctx: &RuntimeContext, cmd: ServeCommand) -> Result<()> {
     }
 
     // Bind and serve
-    let addr: SocketAddr = format!("{}:{}", cmd.host, cmd.port)
-        .parse()
-        .context("invalid address")?;
+    let host = cmd.host.as_deref().unwrap_or(&ctx.config.server.host);
+    let addr = net::bind_addr(host, cmd.port)?;
+    let dual_stack = ctx.config.server.dual_stack;
 
     info!("Listening on http://{}", addr);
 
-    let listener = match TcpListener::bind(addr).await {
+    let listener = match net::bind(addr, dual_stack) {
         Ok(l) => l,
         Err(e) if e.kind() == std::io::ErrorKind::AddrInUse => {
             warn!(
@@ -2850,8 +3902,7 @@ async fn handle_serve(ctx: &RuntimeContext, cmd: ServeCommand) -> Result<()> {
             if try_kill_port_holder(cmd.port).await {
                 // Wait for the port to become available
                 tokio::time::sleep(std::time::Duration::from_secs(2)).await;
-                TcpListener::bind(addr)
-                    .await
+                net::bind(addr, dual_stack)
                     .context("binding to address after killing stale process")?
             } else {
                 return Err(anyhow::anyhow!(
@@ -2864,9 +3915,17 @@ async fn handle_serve(ctx: &RuntimeContext, cmd: ServeCommand) -> Result<()> {
         Err(e) => return Err(e).context("binding to address"),
     };
 
+    // Kept until the server stops; dropping it withdraws the advertisement.
+    let _mdns = mdns::advertise(&ctx.config.mdns, addr, dual_stack).unwrap_or_else(|e| {
+        warn!("mDNS advertisement disabled: {e:#}");
+        None
+    });
+
     let stop_sessions_on_shutdown = !local_mode || ctx.config.local.stop_sessions_on_shutdown;
 
     // Set up graceful shutdown
+    let restart_requested = Arc::new(std::sync::atomic::AtomicBool::new(false));
+    let restart_flag = restart_requested.clone();
     let shutdown_signal = async move {
         let ctrl_c = async {
             if let Err(err) = tokio::signal::ctrl_c().await {
@@ -2892,8 +3951,16 @@ async fn handle_serve(ctx: &RuntimeContext, cmd: ServeCommand) -> Result<()> {
         tokio::select! {
             _ = ctrl_c => {},
             _ = terminate => {},
+            _ = config_restart.notified() => {
+                restart_flag.store(true, std::sync::atomic::Ordering::SeqCst);
+            },
         }
 
+        // Close every WebSocket with a jittered reconnect hint, and give the
+        // close frames a moment to go out before the listener stops.
+        reconnect.begin_shutdown();
+        tokio::time::sleep(std::time::Duration::from_millis(500)).await;
+
         info!("Shutdown signal received, stopping containers...");
 
         // Kill browser daemon processes
@@ -2926,6 +3993,11 @@ async fn handle_serve(ctx: &RuntimeContext, cmd: ServeCommand) -> Result<()> {
     .await
     .context("running server")?;
 
+    if restart_requested.load(std::sync::atomic::Ordering::SeqCst) {
+        info!("Exiting to apply remote config");
+        std::process::exit(remote_config::RESTART_EXIT_CODE);
+    }
+
     Ok(())
 }
 
@@ -3051,6 +4123,8 @@ fn admin_socket_user() -> auth::CurrentUser {
             preferred_username: Some("admin-socket".to_string()),
             roles: vec![auth::Role::Admin.to_string()],
             role: Some(auth::Role::Admin.to_string()),
+            act: None,
+            scope: None,
         },
     }
 }
@@ -3261,7 +4335,11 @@ async fn shutdown_all_sessions(session_service: &session::SessionService) -> Res
     Ok(())
 }
 
-fn load_or_init_config(paths: &mut AppPaths, common: &CommonOpts) -> Result<AppConfig> {
+fn load_or_init_config(
+    paths: &mut AppPaths,
+    common: &CommonOpts,
+    remote_url: Option<&str>,
+) -> Result<(AppConfig, Option<remote_config::RemoteConfigSource>)> {
     if !paths.config_file.exists() {
         if common.dry_run {
             info!(
@@ -3273,8 +4351,45 @@ fn load_or_init_config(paths: &mut AppPaths, common: &CommonOpts) -> Result<AppC
         }
     }
 
+    // The bundle location and trust key come from the local config only, so a
+    // bundle cannot replace the key it is verified with.
+    let local = build_config(paths, None)?;
+    let remote = remote_config_source(&local, paths, common, remote_url)?;
+    let bundle = match remote.as_ref().map(|source| source.load_cached()) {
+        Some(Ok(bundle)) => bundle,
+        Some(Err(e)) => {
+            warn!("Ignoring cached remote config: {e:#}");
+            None
+        }
+        None => None,
+    };
+    let mut config = match bundle {
+        Some(bundle) => build_config(paths, Some(&bundle))?,
+        None => local,
+    };
+
+    if let Some(ref file) = config.logging.file {
+        let expanded = expand_str_path(file)?;
+        config.logging.file = Some(expanded.display().to_string());
+    }
+
+    if let Some(ref file) = config.logging.audit_file {
+        let expanded = expand_str_path(file)?;
+        config.logging.audit_file = Some(expanded.display().to_string());
+    }
+
+    if let Some(ref socket) = config.server.admin_socket_path {
+        let expanded = expand_str_path(socket)?;
+        config.server.admin_socket_path = Some(expanded.display().to_string());
+    }
+
+    Ok((config, remote))
+}
+
+/// Layer defaults, the config file, a remote bundle and the environment.
+fn build_config(paths: &AppPaths, remote_bundle: Option<&str>) -> Result<AppConfig> {
     let env_prefix = env_prefix();
-    let built = Config::builder()
+    let mut builder = Config::builder()
         .set_default("profile", "default")?
         .set_default("logging.level", "info")?
         .set_default("runtime.parallelism", default_parallelism() as i64)?
@@ -3284,28 +4399,192 @@ fn load_or_init_config(paths: &mut AppPaths, common: &CommonOpts) -> Result<AppC
             File::from(paths.config_file.as_path())
                 .format(FileFormat::Toml)
                 .required(false),
-        )
+        );
+    if let Some(bundle) = remote_bundle {
+        builder = builder.add_source(File::from_str(bundle, FileFormat::Toml));
+    }
+    let built = builder
         .add_source(Environment::with_prefix(env_prefix.as_str()).separator("__"))
         .build()?;
+    Ok(built.try_deserialize()?)
+}
 
-    let mut config: AppConfig = built.try_deserialize()?;
+/// Remote bundle from `--config https://...` or `[config] remote_url`.
+fn remote_config_source(
+    config: &AppConfig,
+    paths: &AppPaths,
+    common: &CommonOpts,
+    remote_url: Option<&str>,
+) -> Result<Option<remote_config::RemoteConfigSource>> {
+    let url = remote_url.or(Some(config.config.remote_url.as_str()).filter(|url| !url.is_empty()));
+    let Some(url) = url else {
+        return Ok(None);
+    };
+    let public_key = common
+        .config_public_key
+        .as_deref()
+        .unwrap_or(&config.config.public_key);
+    let cache_dir = paths
+        .config_file
+        .parent()
+        .ok_or_else(|| anyhow!("invalid config file path: {:?}", paths.config_file))?;
+    remote_config::RemoteConfigSource::new(url, public_key, cache_dir).map(Some)
+}
 
-    if let Some(ref file) = config.logging.file {
-        let expanded = expand_str_path(file)?;
-        config.logging.file = Some(expanded.display().to_string());
+/// Record the startup state of each optional subsystem and return the
+/// endpoints to keep probing.
+fn register_capabilities(
+    config: &AppConfig,
+    registry: &capabilities::CapabilityRegistry,
+    multi_user: bool,
+    eavs_client: bool,
+    sldr_users: bool,
+) -> Vec<capabilities::ProbeTarget> {
+    use capabilities::{ProbeTarget, Subsystem};
+
+    let mut targets = Vec::new();
+
+    if !config.mmry.enabled {
+        registry.disable(Subsystem::Mmry, "mmry.enabled is false");
+    } else if multi_user && !config.local.linux_users.enabled {
+        registry.degrade(
+            Subsystem::Mmry,
+            "per-user mmry instances require local.linux_users.enabled",
+        );
+    } else {
+        registry.enable(Subsystem::Mmry);
+        if config.local.single_user {
+            targets.push(ProbeTarget::fixed(
+                Subsystem::Mmry,
+                "mmry service",
+                config.mmry.local_service_url.clone(),
+            ));
+        }
     }
 
-    if let Some(ref file) = config.logging.audit_file {
-        let expanded = expand_str_path(file)?;
-        config.logging.audit_file = Some(expanded.display().to_string());
+    // Voice endpoints are probed by the voice health poller, which follows
+    // reloaded settings.
+    if config.voice.enabled {
+        registry.enable(Subsystem::Voice);
+    } else {
+        registry.disable(Subsystem::Voice, "voice.enabled is false");
     }
 
-    if let Some(ref socket) = config.server.admin_socket_path {
-        let expanded = expand_str_path(socket)?;
-        config.server.admin_socket_path = Some(expanded.display().to_string());
+    // Per-user hstry runs behind the runners; single-user reads its database.
+    if multi_user || history::hstry_db_path().is_some() {
+        registry.enable(Subsystem::Hstry);
+    } else {
+        registry.degrade(
+            Subsystem::Hstry,
+            "hstry database not found; chat history is read from session files",
+        );
+    }
+
+    match config.eavs.as_ref() {
+        None => registry.disable(Subsystem::Eavs, "eavs is not configured"),
+        Some(eavs) if !eavs.enabled => registry.disable(Subsystem::Eavs, "eavs.enabled is false"),
+        Some(_) if !eavs_client => registry.degrade(
+            Subsystem::Eavs,
+            "no usable master key (set eavs.master_key or EAVS_MASTER_KEY)",
+        ),
+        Some(eavs) => {
+            registry.enable(Subsystem::Eavs);
+            targets.push(ProbeTarget::fixed(
+                Subsystem::Eavs,
+                "EAVS",
+                eavs.base_url.clone(),
+            ));
+        }
+    }
+
+    if !config.sldr.enabled {
+        registry.disable(Subsystem::Sldr, "sldr.enabled is false");
+    } else if sldr_users {
+        registry.enable(Subsystem::Sldr);
+    } else {
+        registry.degrade(
+            Subsystem::Sldr,
+            "per-user sldr instances require local multi-user mode with local.linux_users.enabled",
+        );
+    }
+
+    targets
+}
+
+/// Voice state for the API layer.
+fn voice_state(config: &VoiceConfig) -> api::VoiceState {
+    api::VoiceState {
+        enabled: config.enabled,
+        stt_url: config.stt_url.clone(),
+        tts_url: config.tts_url.clone(),
+        vad_timeout_ms: config.vad_timeout_ms,
+        default_voice: config.default_voice.clone(),
+        default_speed: config.default_speed,
+        auto_language_detect: config.auto_language_detect,
+        tts_muted: config.tts_muted,
+        continuous_mode: config.continuous_mode,
+        default_visualizer: config.default_visualizer.clone(),
+        interrupt_word_count: config.interrupt_word_count,
+        interrupt_backoff_ms: config.interrupt_backoff_ms,
+        visualizer_voices: config
+            .visualizer_voices
+            .iter()
+            .map(|(k, v)| {
+                (
+                    k.clone(),
+                    api::VisualizerVoiceState {
+                        voice: v.voice.clone(),
+                        speed: v.speed,
+                    },
+                )
+            })
+            .collect(),
     }
+}
 
-    Ok(config)
+/// The part of the config that a reload applies to running services.
+fn live_config(config: &AppConfig) -> config_reload::LiveConfig {
+    config_reload::LiveConfig {
+        log_level: config.logging.level.clone(),
+        sessions: session::SessionLimits {
+            max_concurrent_sessions: config.sessions.max_concurrent_sessions,
+            idle_timeout_minutes: config.sessions.idle_timeout_minutes,
+            idle_action: config.sessions.idle_action,
+            default_session_budget_usd: config
+                .eavs
+                .as_ref()
+                .and_then(|e| e.default_session_budget_usd),
+            default_session_rpm: config.eavs.as_ref().and_then(|e| e.default_session_rpm),
+        },
+        voice: voice_state(&config.voice),
+    }
+}
+
+fn loaded_config(config: &AppConfig) -> Result<config_reload::LoadedConfig> {
+    Ok(config_reload::LoadedConfig {
+        live: live_config(config),
+        full: toml::Value::try_from(config).context("serializing config")?,
+    })
+}
+
+/// Tracing filter for a log level.
+fn log_filter(level: LevelFilter) -> tracing_subscriber::EnvFilter {
+    let level = level.as_str().to_lowercase();
+    tracing_subscriber::EnvFilter::new(format!("oqto={level},tower_http={level}"))
+}
+
+/// Apply a reloaded `logging.level`.
+fn set_log_level(level: &str) -> Result<()> {
+    let level: LevelFilter = level
+        .parse()
+        .map_err(|_| anyhow!("invalid log level {level:?}"))?;
+    LOG_FILTER
+        .get()
+        .context("logging is not initialized")?
+        .reload(log_filter(level))
+        .context("updating log filter")?;
+    log::set_max_level(level);
+    Ok(())
 }
 
 fn write_default_config(path: &Path) -> Result<()> {
@@ -6,9 +6,10 @@
 use std::collections::HashMap;
 use std::sync::Arc;
 
+use comrak::nodes::{AstNode, NodeValue};
 use comrak::options::Plugins;
 use comrak::plugins::syntect::SyntectAdapter;
-use comrak::{Options, markdown_to_html_with_plugins};
+use comrak::{Arena, Options, markdown_to_html_with_plugins, parse_document};
 use once_cell::sync::Lazy;
 use tokio::sync::RwLock;
 
@@ -93,7 +94,7 @@ pub async fn render_markdown(content: &str) -> String {
 }
 
 /// Synchronous markdown rendering (for use in spawn_blocking)
-fn render_markdown_sync(content: &str) -> String {
+pub(crate) fn render_markdown_sync(content: &str) -> String {
     let mut options = Options::default();
     options.extension.strikethrough = true;
     options.extension.table = true;
@@ -109,7 +110,7 @@ fn render_markdown_sync(content: &str) -> String {
 }
 
 /// Escape HTML entities
-fn html_escape(s: &str) -> String {
+pub(crate) fn html_escape(s: &str) -> String {
     s.replace('&', "&amp;")
         .replace('<', "&lt;")
         .replace('>', "&gt;")
@@ -128,6 +129,74 @@ pub async fn render_markdown_batch(contents: Vec<String>) -> Vec<String> {
     futures::future::join_all(futures).await
 }
 
+/// Title and opening paragraph of a README, as plain text.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ReadmeSummary {
+    /// The first level-1 heading, or the first heading of any level.
+    pub title: Option<String>,
+    /// The first top-level paragraph with text of its own (badge rows,
+    /// which are only images, are passed over).
+    pub description: Option<String>,
+}
+
+/// Extract a [`ReadmeSummary`] from markdown.
+pub fn summarize_readme(content: &str) -> ReadmeSummary {
+    let arena = Arena::new();
+    let root = parse_document(&arena, content, &Options::default());
+
+    let mut first_heading = None;
+    let mut title = None;
+    let mut description = None;
+    for node in root.children() {
+        let (is_heading, level) = match &node.data.borrow().value {
+            NodeValue::Heading(heading) => (true, heading.level),
+            NodeValue::Paragraph => (false, 0),
+            _ => continue,
+        };
+        let text = plain_text(node);
+        if text.is_empty() {
+            continue;
+        }
+        if is_heading {
+            if level == 1 && title.is_none() {
+                title = Some(text);
+            } else if first_heading.is_none() {
+                first_heading = Some(text);
+            }
+        } else if description.is_none() {
+            description = Some(text);
+        }
+        if title.is_some() && description.is_some() {
+            break;
+        }
+    }
+
+    ReadmeSummary {
+        title: title.or(first_heading),
+        description,
+    }
+}
+
+/// Text of a node without markup; image alt text and inline HTML are left
+/// out.
+fn plain_text<'a>(node: &'a AstNode<'a>) -> String {
+    fn collect<'a>(node: &'a AstNode<'a>, out: &mut String) {
+        for child in node.children() {
+            match &child.data.borrow().value {
+                NodeValue::Text(text) => out.push_str(text),
+                NodeValue::Code(code) => out.push_str(&code.literal),
+                NodeValue::SoftBreak | NodeValue::LineBreak => out.push(' '),
+                NodeValue::Image(_) | NodeValue::HtmlInline(_) => {}
+                _ => collect(child, out),
+            }
+        }
+    }
+
+    let mut out = String::new();
+    collect(node, &mut out);
+    out.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -154,4 +223,20 @@ mod tests {
         let html2 = render_markdown(content).await;
         assert_eq!(html1, html2);
     }
+
+    #[test]
+    fn test_summarize_readme() {
+        let summary = summarize_readme(
+            "[![CI](https://x/badge.svg)](https://x)\n\n## Setup\n\n# oqto\n\nA *workspace* for\n`pi` agents.\n\nMore.",
+        );
+        assert_eq!(summary.title.as_deref(), Some("oqto"));
+        assert_eq!(
+            summary.description.as_deref(),
+            Some("A workspace for pi agents.")
+        );
+
+        let summary = summarize_readme("## Only a subheading\n");
+        assert_eq!(summary.title.as_deref(), Some("Only a subheading"));
+        assert_eq!(summary.description, None);
+    }
 }
@@ -0,0 +1,166 @@
+//! mDNS advertisement of the server on the local network.
+//!
+//! `oqto serve` registers a `_oqto._tcp` service so the desktop app finds
+//! servers by browsing for it instead of probing address ranges. The TXT
+//! record carries the instance name, the server version and the API path.
+//! Servers bound to a loopback address are not advertised, since nothing
+//! on the network could reach them. A wildcard bind announces the
+//! addresses of the families it accepts: IPv4 for `0.0.0.0`, IPv6 (link-local
+//! included) for `::`, and both for a dual-stack `::`.
+
+use std::net::SocketAddr;
+
+use anyhow::{Context, Result};
+use mdns_sd::{IfKind, ServiceDaemon, ServiceInfo};
+use serde::{Deserialize, Serialize};
+use tracing::{info, warn};
+
+/// Service type browsed for by clients.
+pub const SERVICE_TYPE: &str = "_oqto._tcp.local.";
+
+/// `[mdns]` config section.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MdnsConfig {
+    pub enabled: bool,
+    /// Name shown to clients. Empty uses the host name.
+    pub instance_name: String,
+}
+
+impl Default for MdnsConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            instance_name: String::new(),
+        }
+    }
+}
+
+/// A registered service, withdrawn on drop.
+pub struct Advertisement {
+    daemon: ServiceDaemon,
+    fullname: String,
+}
+
+impl Drop for Advertisement {
+    fn drop(&mut self) {
+        if let Err(e) = self.daemon.unregister(&self.fullname) {
+            warn!("Failed to withdraw mDNS advertisement: {e}");
+        }
+        let _ = self.daemon.shutdown();
+    }
+}
+
+/// Advertise the server listening on `addr`. None when disabled or bound
+/// to loopback.
+pub fn advertise(
+    config: &MdnsConfig,
+    addr: SocketAddr,
+    dual_stack: bool,
+) -> Result<Option<Advertisement>> {
+    let addr = SocketAddr::new(crate::net::canonical_ip(addr.ip()), addr.port());
+    if !config.enabled || addr.ip().is_loopback() {
+        return Ok(None);
+    }
+
+    let host = crate::siem::local_hostname();
+    let instance = instance_name(config, &host);
+    // A wildcard bind is reachable on every interface; otherwise only the
+    // bound address is announced.
+    let ip = if addr.ip().is_unspecified() {
+        String::new()
+    } else {
+        addr.ip().to_string()
+    };
+    let properties = [
+        ("name", instance.as_str()),
+        ("version", env!("CARGO_PKG_VERSION")),
+        ("path", "/api"),
+    ];
+
+    let daemon = ServiceDaemon::new().context("starting mDNS daemon")?;
+    if addr.ip().is_unspecified() {
+        if addr.is_ipv4() {
+            daemon.disable_interface(IfKind::IPv6)?;
+        } else if !dual_stack {
+            daemon.disable_interface(IfKind::IPv4)?;
+        }
+    }
+    let mut service = ServiceInfo::new(
+        SERVICE_TYPE,
+        &instance,
+        &format!("{}.local.", host_label(&host)),
+        ip.as_str(),
+        addr.port(),
+        &properties[..],
+    )
+    .context("building mDNS service info")?;
+    if addr.ip().is_unspecified() {
+        service = service.enable_addr_auto();
+    }
+    let fullname = service.get_fullname().to_string();
+    daemon
+        .register(service)
+        .context("registering mDNS service")?;
+
+    info!(service = %fullname, port = addr.port(), "Advertising server via mDNS");
+    Ok(Some(Advertisement { daemon, fullname }))
+}
+
+fn instance_name(config: &MdnsConfig, host: &str) -> String {
+    let name = config.instance_name.trim();
+    if name.is_empty() {
+        format!("oqto on {host}")
+    } else {
+        name.to_string()
+    }
+}
+
+/// First label of the host name, as used for `.local` names.
+fn host_label(host: &str) -> &str {
+    match host.split('.').next() {
+        Some(label) if !label.is_empty() && label != "-" => label,
+        _ => "oqto",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_names() {
+        let config = MdnsConfig::default();
+        assert_eq!(instance_name(&config, "lab1"), "oqto on lab1");
+        let named = MdnsConfig {
+            instance_name: " Team server ".to_string(),
+            ..Default::default()
+        };
+        assert_eq!(instance_name(&named, "lab1"), "Team server");
+
+        assert_eq!(host_label("lab1.example.com"), "lab1");
+        assert_eq!(host_label("-"), "oqto");
+    }
+
+    #[test]
+    fn test_loopback_is_not_advertised() {
+        let addr: SocketAddr = "127.0.0.1:8080".parse().unwrap();
+        assert!(
+            advertise(&MdnsConfig::default(), addr, true)
+                .unwrap()
+                .is_none()
+        );
+        let mapped: SocketAddr = "[::ffff:127.0.0.1]:8080".parse().unwrap();
+        assert!(
+            advertise(&MdnsConfig::default(), mapped, true)
+                .unwrap()
+                .is_none()
+        );
+        let disabled = MdnsConfig {
+            enabled: false,
+            ..Default::default()
+        };
+        let addr: SocketAddr = "0.0.0.0:8080".parse().unwrap();
+        assert!(advertise(&disabled, addr, true).unwrap().is_none());
+    }
+}
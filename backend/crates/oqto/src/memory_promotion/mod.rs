@@ -0,0 +1,374 @@
+//! Promoting session findings into long-term memory.
+//!
+//! Chats are ephemeral; mmry is where knowledge should outlive them. Nothing
+//! is copied automatically: users select messages or findings from a session
+//! and promote them with `POST /api/sessions/{id}/promote-memory`, and agents
+//! can only *suggest* promotions (`oqtoctl memory suggest`), which wait in a
+//! review queue until the user accepts or rejects them. Every promoted memory
+//! records where it came from under `metadata.provenance` (session, message,
+//! who promoted it and when).
+
+mod models;
+mod repository;
+
+pub use models::{
+    AcceptSuggestionRequest, MemoryPromotionConfig, MemorySuggestion, PromoteMemoryRequest,
+    PromotedMemory, PromotionItem, Provenance, SuggestMemoryRequest, SuggestionListQuery,
+    SuggestionStatus,
+};
+pub use repository::{MemorySuggestionRepository, NewSuggestion};
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::{Result, anyhow, bail};
+use mmry_core::agent_ctx::AgentCtx;
+use mmry_core::memory::MemoryType;
+use mmry_core::memory_file::{MemoryEvent, MemoryFile};
+use tracing::{info, warn};
+
+use crate::ws::{WsEvent, WsHub};
+
+/// Most tags kept per memory.
+const MAX_TAGS: usize = 20;
+/// How often stale suggestions are expired.
+const SWEEP_INTERVAL: Duration = Duration::from_secs(3600);
+
+/// Memory promotion service: direct promotions and the suggestion queue.
+pub struct MemoryPromotionService {
+    repo: MemorySuggestionRepository,
+    config: MemoryPromotionConfig,
+    hub: Arc<WsHub>,
+}
+
+impl MemoryPromotionService {
+    pub fn new(
+        repo: MemorySuggestionRepository,
+        config: MemoryPromotionConfig,
+        hub: Arc<WsHub>,
+    ) -> Self {
+        Self { repo, config, hub }
+    }
+
+    pub fn config(&self) -> &MemoryPromotionConfig {
+        &self.config
+    }
+
+    pub fn repository(&self) -> &MemorySuggestionRepository {
+        &self.repo
+    }
+
+    /// Validate an item and merge the request-wide tags into it. `content`
+    /// must already be resolved.
+    pub fn prepare_item(
+        &self,
+        mut item: PromotionItem,
+        shared_tags: &[String],
+    ) -> Result<PromotionItem> {
+        let content = item
+            .content
+            .as_deref()
+            .map(str::trim)
+            .filter(|c| !c.is_empty())
+            .ok_or_else(|| anyhow!("memory content is empty"))?;
+        if content.len() > self.config.max_content_bytes {
+            bail!(
+                "memory content exceeds {} bytes",
+                self.config.max_content_bytes
+            );
+        }
+        item.content = Some(content.to_string());
+
+        let mut tags: Vec<String> = Vec::new();
+        for tag in item.tags.iter().chain(shared_tags) {
+            let tag = tag.trim();
+            if !tag.is_empty() && !tags.iter().any(|t| t == tag) {
+                tags.push(tag.to_string());
+            }
+        }
+        if tags.len() > MAX_TAGS {
+            bail!("at most {MAX_TAGS} tags per memory");
+        }
+        item.tags = tags;
+
+        if let Some(importance) = item.importance
+            && !(1..=10).contains(&importance)
+        {
+            bail!("importance must be between 1 and 10");
+        }
+        if let Some(memory_type) = &item.memory_type
+            && !matches!(memory_type.as_str(), "semantic" | "episodic" | "procedural")
+        {
+            bail!("memory_type must be semantic, episodic or procedural");
+        }
+        Ok(item)
+    }
+
+    /// Write prepared items from a session into the workspace's mmry store.
+    pub fn promote(
+        &self,
+        user_id: &str,
+        session_id: &str,
+        workspace_path: &str,
+        items: &[PromotionItem],
+    ) -> Result<Vec<PromotedMemory>> {
+        let memory_file = open_memory_file(workspace_path)?;
+        let promoted = items
+            .iter()
+            .map(|item| {
+                let provenance = Provenance {
+                    source: "session",
+                    session_id: session_id.to_string(),
+                    message_id: item.message_id.clone(),
+                    suggestion_id: None,
+                    promoted_by: user_id.to_string(),
+                    promoted_at: chrono::Utc::now().to_rfc3339(),
+                };
+                write_memory(&memory_file, item, &provenance)
+            })
+            .collect::<Result<Vec<_>>>()?;
+        info!(
+            user_id = %user_id,
+            session_id = %session_id,
+            count = promoted.len(),
+            "Promoted session findings to memory"
+        );
+        Ok(promoted)
+    }
+
+    /// Queue an agent suggestion and tell the user it is waiting for review.
+    pub async fn suggest(&self, suggestion: &NewSuggestion<'_>) -> Result<MemorySuggestion> {
+        let suggestion = self.repo.create(suggestion).await?;
+        info!(
+            suggestion_id = %suggestion.id,
+            user_id = %suggestion.user_id,
+            session_id = %suggestion.session_id,
+            "Memory promotion suggested"
+        );
+        self.hub
+            .send_to_user(
+                &suggestion.user_id,
+                WsEvent::Notification {
+                    level: "info".to_string(),
+                    title: "Memory suggested".to_string(),
+                    message: format!(
+                        "The agent suggests remembering: {}",
+                        preview(&suggestion.content)
+                    ),
+                    category: "memory.suggestion".to_string(),
+                    detail: serde_json::to_value(&suggestion).ok(),
+                },
+            )
+            .await;
+        Ok(suggestion)
+    }
+
+    /// The suggestion with the reviewer's edits applied, validated.
+    pub fn accepted_item(
+        &self,
+        suggestion: &MemorySuggestion,
+        edits: AcceptSuggestionRequest,
+    ) -> Result<PromotionItem> {
+        self.prepare_item(
+            PromotionItem {
+                content: edits.content.or_else(|| Some(suggestion.content.clone())),
+                message_id: suggestion.message_id.clone(),
+                tags: edits.tags.unwrap_or_else(|| suggestion.tags.clone()),
+                category: edits.category.or_else(|| suggestion.category.clone()),
+                importance: edits.importance.or(suggestion.importance),
+                memory_type: suggestion.memory_type.clone(),
+            },
+            &[],
+        )
+    }
+
+    /// Write an accepted suggestion to mmry and close it. Returns None if it
+    /// was reviewed concurrently; the memory is withdrawn again in that case.
+    /// The caller has checked that the suggestion belongs to `user_id`.
+    pub async fn accept(
+        &self,
+        suggestion: &MemorySuggestion,
+        item: &PromotionItem,
+        user_id: &str,
+    ) -> Result<Option<PromotedMemory>> {
+        let memory_file = open_memory_file(&suggestion.workspace_path)?;
+        let provenance = Provenance {
+            source: "agent_suggestion",
+            session_id: suggestion.session_id.clone(),
+            message_id: suggestion.message_id.clone(),
+            suggestion_id: Some(suggestion.id.clone()),
+            promoted_by: user_id.to_string(),
+            promoted_at: chrono::Utc::now().to_rfc3339(),
+        };
+        let memory = write_memory(&memory_file, item, &provenance)?;
+        if !self
+            .repo
+            .close(
+                &suggestion.id,
+                SuggestionStatus::Accepted,
+                Some(&memory.memory_id),
+            )
+            .await?
+        {
+            let event = MemoryEvent::deprecate(memory.memory_id.clone(), &AgentCtx::from_env());
+            memory_file
+                .append(&event)
+                .map_err(|e| anyhow!("withdrawing memory {}: {e:?}", memory.memory_id))?;
+            return Ok(None);
+        }
+        info!(
+            suggestion_id = %suggestion.id,
+            memory_id = %memory.memory_id,
+            "Memory suggestion accepted"
+        );
+        Ok(Some(memory))
+    }
+
+    /// Decline a pending suggestion.
+    pub async fn reject(&self, suggestion: &MemorySuggestion) -> Result<bool> {
+        let rejected = self
+            .repo
+            .close(&suggestion.id, SuggestionStatus::Rejected, None)
+            .await?;
+        if rejected {
+            info!(suggestion_id = %suggestion.id, "Memory suggestion rejected");
+        }
+        Ok(rejected)
+    }
+
+    /// Expire suggestions nobody reviewed, hourly.
+    pub fn start_sweep_task(self: &Arc<Self>) {
+        let service = Arc::clone(self);
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(SWEEP_INTERVAL);
+            interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+            loop {
+                interval.tick().await;
+                match service
+                    .repo
+                    .expire_stale(service.config.suggestion_ttl_days)
+                    .await
+                {
+                    Ok(0) => {}
+                    Ok(n) => info!(expired = n, "Expired memory suggestions"),
+                    Err(e) => warn!("Memory suggestion sweep failed: {:#}", e),
+                }
+            }
+        });
+    }
+}
+
+fn open_memory_file(workspace_path: &str) -> Result<MemoryFile> {
+    if workspace_path.trim().is_empty() {
+        bail!("session has no workspace");
+    }
+    let memory_file = MemoryFile::open_workspace(workspace_path);
+    memory_file
+        .init(false)
+        .map_err(|e| anyhow!("initializing workspace memory file: {e:?}"))?;
+    Ok(memory_file)
+}
+
+fn write_memory(
+    memory_file: &MemoryFile,
+    item: &PromotionItem,
+    provenance: &Provenance,
+) -> Result<PromotedMemory> {
+    let memory_type = match item.memory_type.as_deref() {
+        Some("episodic") => MemoryType::Episodic,
+        Some("procedural") => MemoryType::Procedural,
+        _ => MemoryType::Semantic,
+    };
+    let mut event = MemoryEvent::add(
+        item.content.clone().unwrap_or_default(),
+        memory_type,
+        item.tags.clone(),
+        &AgentCtx::from_env(),
+    );
+    if let Some(category) = &item.category {
+        event.metadata["category"] = serde_json::Value::String(category.clone());
+    }
+    if let Some(importance) = item.importance {
+        event.metadata["importance"] = serde_json::Value::Number(importance.into());
+    }
+    event.metadata["provenance"] = serde_json::to_value(provenance)?;
+    memory_file
+        .append(&event)
+        .map_err(|e| anyhow!("appending memory event: {e:?}"))?;
+
+    let entry = memory_file
+        .active_memories()
+        .map_err(|e| anyhow!("reading workspace memories: {e:?}"))?
+        .into_iter()
+        .find(|m| m.memory_id == event.memory_id)
+        .ok_or_else(|| anyhow!("memory {} missing after append", event.memory_id))?;
+    Ok(PromotedMemory {
+        memory_id: entry.memory_id,
+        content: entry.content,
+        tags: entry.tags,
+        metadata: entry.metadata,
+        created_at: entry.created_at.to_rfc3339(),
+    })
+}
+
+/// First line of `content`, shortened for notifications.
+fn preview(content: &str) -> String {
+    let line = content.lines().next().unwrap_or_default();
+    match line.char_indices().nth(120) {
+        Some((end, _)) => format!("{}…", &line[..end]),
+        None => line.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::Database;
+
+    async fn service() -> MemoryPromotionService {
+        let db = Database::in_memory().await.unwrap();
+        MemoryPromotionService::new(
+            MemorySuggestionRepository::new(db.pool().clone()),
+            MemoryPromotionConfig::default(),
+            Arc::new(WsHub::new()),
+        )
+    }
+
+    #[tokio::test]
+    async fn test_prepare_item_merges_tags_and_validates() {
+        let service = service().await;
+        let item = service
+            .prepare_item(
+                PromotionItem {
+                    content: Some("  Deploys need the VPN  ".to_string()),
+                    tags: vec!["ops".to_string(), " ".to_string()],
+                    ..Default::default()
+                },
+                &["ops".to_string(), "from-chat".to_string()],
+            )
+            .unwrap();
+        assert_eq!(item.content.as_deref(), Some("Deploys need the VPN"));
+        assert_eq!(item.tags, vec!["ops", "from-chat"]);
+
+        assert!(service.prepare_item(PromotionItem::default(), &[]).is_err());
+        assert!(
+            service
+                .prepare_item(
+                    PromotionItem {
+                        content: Some("x".to_string()),
+                        memory_type: Some("dream".to_string()),
+                        ..Default::default()
+                    },
+                    &[],
+                )
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn test_preview_truncates_first_line() {
+        assert_eq!(preview("short\nsecond"), "short");
+        let long = "a".repeat(200);
+        assert_eq!(preview(&long).chars().count(), 121);
+    }
+}
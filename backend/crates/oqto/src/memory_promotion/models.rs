@@ -0,0 +1,154 @@
+use serde::{Deserialize, Serialize};
+
+/// Memory promotion configuration.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MemoryPromotionConfig {
+    /// Allow promoting session messages into mmry and agent suggestions.
+    pub enabled: bool,
+    /// Most pending suggestions one session may queue.
+    pub max_pending_per_session: i64,
+    /// Pending suggestions not reviewed within this window expire.
+    pub suggestion_ttl_days: i64,
+    /// Longest memory content accepted, in bytes.
+    pub max_content_bytes: usize,
+}
+
+impl Default for MemoryPromotionConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            max_pending_per_session: 50,
+            suggestion_ttl_days: 30,
+            max_content_bytes: 16 * 1024,
+        }
+    }
+}
+
+/// Review state of an agent suggestion.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, sqlx::Type)]
+#[serde(rename_all = "snake_case")]
+#[sqlx(rename_all = "snake_case")]
+pub enum SuggestionStatus {
+    /// Waiting for the user's review.
+    Pending,
+    /// Written to mmry (`memory_id` is set).
+    Accepted,
+    /// Declined by the user.
+    Rejected,
+    /// Not reviewed within `suggestion_ttl_days`.
+    Expired,
+}
+
+/// A memory the agent proposed for promotion.
+#[derive(Debug, Clone, Serialize)]
+pub struct MemorySuggestion {
+    pub id: String,
+    pub user_id: String,
+    pub session_id: String,
+    /// Workspace whose mmry store the memory goes into.
+    pub workspace_path: String,
+    /// Message the finding came from, if any.
+    pub message_id: Option<String>,
+    pub content: String,
+    pub tags: Vec<String>,
+    pub category: Option<String>,
+    pub importance: Option<i32>,
+    pub memory_type: Option<String>,
+    /// Why the agent thinks this is worth keeping.
+    pub reason: Option<String>,
+    pub status: SuggestionStatus,
+    /// Memory created when the suggestion was accepted.
+    pub memory_id: Option<String>,
+    pub created_at: String,
+    pub reviewed_at: Option<String>,
+}
+
+/// One memory to write, from a selected message or finding.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct PromotionItem {
+    /// Memory text. Defaults to the text of `message_id`.
+    #[serde(default)]
+    pub content: Option<String>,
+    /// Message the memory is taken from.
+    #[serde(default)]
+    pub message_id: Option<String>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    #[serde(default)]
+    pub category: Option<String>,
+    #[serde(default)]
+    pub importance: Option<i32>,
+    /// `semantic` (default), `episodic` or `procedural`.
+    #[serde(default)]
+    pub memory_type: Option<String>,
+}
+
+/// Request body for `POST /api/sessions/{id}/promote-memory`.
+#[derive(Debug, Clone, Deserialize)]
+pub struct PromoteMemoryRequest {
+    pub items: Vec<PromotionItem>,
+    /// Tags added to every item.
+    #[serde(default)]
+    pub tags: Vec<String>,
+}
+
+/// Request body for an agent suggestion.
+#[derive(Debug, Clone, Deserialize)]
+pub struct SuggestMemoryRequest {
+    #[serde(flatten)]
+    pub item: PromotionItem,
+    #[serde(default)]
+    pub reason: Option<String>,
+}
+
+/// Optional edits applied when accepting a suggestion.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct AcceptSuggestionRequest {
+    #[serde(default)]
+    pub content: Option<String>,
+    /// Replaces the suggested tags.
+    #[serde(default)]
+    pub tags: Option<Vec<String>>,
+    #[serde(default)]
+    pub category: Option<String>,
+    #[serde(default)]
+    pub importance: Option<i32>,
+}
+
+/// Filters for the review queue.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct SuggestionListQuery {
+    /// Defaults to pending suggestions.
+    #[serde(default)]
+    pub status: Option<SuggestionStatus>,
+    #[serde(default)]
+    pub session_id: Option<String>,
+    #[serde(default)]
+    pub limit: Option<i64>,
+}
+
+/// Where a promoted memory came from; stored under `metadata.provenance`.
+#[derive(Debug, Clone, Serialize)]
+pub struct Provenance {
+    /// `session` for direct promotions, `agent_suggestion` for accepted
+    /// suggestions.
+    pub source: &'static str,
+    pub session_id: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub message_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub suggestion_id: Option<String>,
+    pub promoted_by: String,
+    pub promoted_at: String,
+}
+
+/// A memory written to mmry by a promotion.
+#[derive(Debug, Clone, Serialize)]
+pub struct PromotedMemory {
+    pub memory_id: String,
+    pub content: String,
+    pub tags: Vec<String>,
+    pub metadata: serde_json::Value,
+    pub created_at: String,
+}
@@ -0,0 +1,279 @@
+use anyhow::{Context, Result};
+use sqlx::{FromRow, QueryBuilder, Sqlite, SqlitePool};
+
+use super::{MemorySuggestion, SuggestionListQuery, SuggestionStatus};
+
+const SUGGESTION_COLUMNS: &str = "id, user_id, session_id, workspace_path, message_id, content, \
+     tags, category, importance, memory_type, reason, status, memory_id, created_at, reviewed_at";
+
+/// Most suggestions returned by one list call.
+const MAX_LIST_LIMIT: i64 = 200;
+
+#[derive(Debug, Clone, FromRow)]
+struct SuggestionRow {
+    id: String,
+    user_id: String,
+    session_id: String,
+    workspace_path: String,
+    message_id: Option<String>,
+    content: String,
+    tags: String,
+    category: Option<String>,
+    importance: Option<i32>,
+    memory_type: Option<String>,
+    reason: Option<String>,
+    status: SuggestionStatus,
+    memory_id: Option<String>,
+    created_at: String,
+    reviewed_at: Option<String>,
+}
+
+impl From<SuggestionRow> for MemorySuggestion {
+    fn from(row: SuggestionRow) -> Self {
+        Self {
+            id: row.id,
+            user_id: row.user_id,
+            session_id: row.session_id,
+            workspace_path: row.workspace_path,
+            message_id: row.message_id,
+            content: row.content,
+            tags: serde_json::from_str(&row.tags).unwrap_or_default(),
+            category: row.category,
+            importance: row.importance,
+            memory_type: row.memory_type,
+            reason: row.reason,
+            status: row.status,
+            memory_id: row.memory_id,
+            created_at: row.created_at,
+            reviewed_at: row.reviewed_at,
+        }
+    }
+}
+
+/// Fields for a new pending suggestion.
+#[derive(Debug, Clone)]
+pub struct NewSuggestion<'a> {
+    pub user_id: &'a str,
+    pub session_id: &'a str,
+    pub workspace_path: &'a str,
+    pub message_id: Option<&'a str>,
+    pub content: &'a str,
+    pub tags: &'a [String],
+    pub category: Option<&'a str>,
+    pub importance: Option<i32>,
+    pub memory_type: Option<&'a str>,
+    pub reason: Option<&'a str>,
+}
+
+#[derive(Debug, Clone)]
+pub struct MemorySuggestionRepository {
+    pool: SqlitePool,
+}
+
+impl MemorySuggestionRepository {
+    pub fn new(pool: SqlitePool) -> Self {
+        Self { pool }
+    }
+
+    fn generate_id() -> String {
+        format!("msg_{}", nanoid::nanoid!(12))
+    }
+
+    pub async fn create(&self, suggestion: &NewSuggestion<'_>) -> Result<MemorySuggestion> {
+        let id = Self::generate_id();
+        let tags = serde_json::to_string(suggestion.tags).unwrap_or_else(|_| "[]".to_string());
+        sqlx::query(
+            r#"INSERT INTO memory_suggestions
+                   (id, user_id, session_id, workspace_path, message_id, content, tags,
+                    category, importance, memory_type, reason)
+               VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)"#,
+        )
+        .bind(&id)
+        .bind(suggestion.user_id)
+        .bind(suggestion.session_id)
+        .bind(suggestion.workspace_path)
+        .bind(suggestion.message_id)
+        .bind(suggestion.content)
+        .bind(&tags)
+        .bind(suggestion.category)
+        .bind(suggestion.importance)
+        .bind(suggestion.memory_type)
+        .bind(suggestion.reason)
+        .execute(&self.pool)
+        .await
+        .context("insert memory suggestion")?;
+
+        self.get(&id)
+            .await?
+            .ok_or_else(|| anyhow::anyhow!("Suggestion not found after creation"))
+    }
+
+    pub async fn get(&self, id: &str) -> Result<Option<MemorySuggestion>> {
+        let row = sqlx::query_as::<_, SuggestionRow>(&format!(
+            "SELECT {SUGGESTION_COLUMNS} FROM memory_suggestions WHERE id = ?"
+        ))
+        .bind(id)
+        .fetch_optional(&self.pool)
+        .await
+        .context("get memory suggestion")?;
+        Ok(row.map(Into::into))
+    }
+
+    /// A user's suggestions, newest first.
+    pub async fn list_for_user(
+        &self,
+        user_id: &str,
+        query: &SuggestionListQuery,
+    ) -> Result<Vec<MemorySuggestion>> {
+        let mut qb = QueryBuilder::<Sqlite>::new(format!(
+            "SELECT {SUGGESTION_COLUMNS} FROM memory_suggestions WHERE user_id = "
+        ));
+        qb.push_bind(user_id.to_string());
+        qb.push(" AND status = ")
+            .push_bind(query.status.unwrap_or(SuggestionStatus::Pending));
+        if let Some(session_id) = &query.session_id {
+            qb.push(" AND session_id = ").push_bind(session_id.clone());
+        }
+        qb.push(" ORDER BY created_at DESC, id DESC LIMIT ")
+            .push_bind(query.limit.unwrap_or(50).clamp(1, MAX_LIST_LIMIT));
+
+        let rows = qb
+            .build_query_as::<SuggestionRow>()
+            .fetch_all(&self.pool)
+            .await
+            .context("list memory suggestions")?;
+        Ok(rows.into_iter().map(Into::into).collect())
+    }
+
+    pub async fn count_pending(&self, session_id: &str) -> Result<i64> {
+        let (count,) = sqlx::query_as::<_, (i64,)>(
+            "SELECT COUNT(*) FROM memory_suggestions WHERE session_id = ? AND status = 'pending'",
+        )
+        .bind(session_id)
+        .fetch_one(&self.pool)
+        .await
+        .context("count pending memory suggestions")?;
+        Ok(count)
+    }
+
+    /// Close a pending suggestion. Returns false if it was already reviewed.
+    pub async fn close(
+        &self,
+        id: &str,
+        status: SuggestionStatus,
+        memory_id: Option<&str>,
+    ) -> Result<bool> {
+        let result = sqlx::query(
+            r#"UPDATE memory_suggestions
+               SET status = ?, memory_id = ?, reviewed_at = datetime('now')
+               WHERE id = ? AND status = 'pending'"#,
+        )
+        .bind(status)
+        .bind(memory_id)
+        .bind(id)
+        .execute(&self.pool)
+        .await
+        .context("close memory suggestion")?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Expire pending suggestions older than `ttl_days`.
+    pub async fn expire_stale(&self, ttl_days: i64) -> Result<u64> {
+        let result = sqlx::query(
+            r#"UPDATE memory_suggestions
+               SET status = 'expired', reviewed_at = datetime('now')
+               WHERE status = 'pending' AND created_at <= datetime('now', ?)"#,
+        )
+        .bind(format!("-{ttl_days} days"))
+        .execute(&self.pool)
+        .await
+        .context("expire memory suggestions")?;
+        Ok(result.rows_affected())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::Database;
+
+    async fn repo() -> MemorySuggestionRepository {
+        let db = Database::in_memory().await.unwrap();
+        sqlx::query("INSERT INTO users (id, username, email, display_name) VALUES (?, ?, ?, ?)")
+            .bind("alice")
+            .bind("alice")
+            .bind("alice@example.com")
+            .bind("Alice")
+            .execute(db.pool())
+            .await
+            .unwrap();
+        MemorySuggestionRepository::new(db.pool().clone())
+    }
+
+    fn suggestion<'a>(content: &'a str, tags: &'a [String]) -> NewSuggestion<'a> {
+        NewSuggestion {
+            user_id: "alice",
+            session_id: "ses_1",
+            workspace_path: "/home/alice/oqto",
+            message_id: Some("m1"),
+            content,
+            tags,
+            category: None,
+            importance: None,
+            memory_type: None,
+            reason: Some("recurring build fix"),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_suggestion_review_lifecycle() {
+        let repo = repo().await;
+        let tags = vec!["build".to_string()];
+        let first = repo
+            .create(&suggestion("cargo needs --locked in CI", &tags))
+            .await
+            .unwrap();
+        let second = repo.create(&suggestion("use bun", &[])).await.unwrap();
+        assert_eq!(first.status, SuggestionStatus::Pending);
+        assert_eq!(first.tags, tags);
+        assert_eq!(repo.count_pending("ses_1").await.unwrap(), 2);
+
+        assert!(
+            repo.close(&first.id, SuggestionStatus::Accepted, Some("mem_1"))
+                .await
+                .unwrap()
+        );
+        // Reviewing twice is refused.
+        assert!(
+            !repo
+                .close(&first.id, SuggestionStatus::Rejected, None)
+                .await
+                .unwrap()
+        );
+        let accepted = repo.get(&first.id).await.unwrap().unwrap();
+        assert_eq!(accepted.status, SuggestionStatus::Accepted);
+        assert_eq!(accepted.memory_id.as_deref(), Some("mem_1"));
+
+        let pending = repo
+            .list_for_user("alice", &SuggestionListQuery::default())
+            .await
+            .unwrap();
+        assert_eq!(pending.len(), 1);
+        assert_eq!(pending[0].id, second.id);
+
+        let accepted = repo
+            .list_for_user(
+                "alice",
+                &SuggestionListQuery {
+                    status: Some(SuggestionStatus::Accepted),
+                    ..Default::default()
+                },
+            )
+            .await
+            .unwrap();
+        assert_eq!(accepted.len(), 1);
+
+        assert_eq!(repo.expire_stale(0).await.unwrap(), 1);
+        assert_eq!(repo.count_pending("ses_1").await.unwrap(), 0);
+    }
+}
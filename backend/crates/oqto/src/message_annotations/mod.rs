@@ -0,0 +1,18 @@
+//! Reactions and annotations on agent responses.
+//!
+//! Users rate a response (thumbs up or down), flag it (e.g.
+//! `hallucination`) or attach a free-text note. Each annotation records the
+//! provider and model that produced the message, so the analytics endpoints
+//! can report quality signals per model from feedback on individual
+//! responses. Like bookmarks, annotations in shared workspace sessions are
+//! visible to every member with `chat_read`; those in personal sessions
+//! only to their author.
+
+mod models;
+mod repository;
+
+pub use models::{
+    AnnotationKind, CreateAnnotationRequest, FeedbackStatsQuery, MessageAnnotation,
+    ModelFeedbackStat,
+};
+pub use repository::{AnnotationRepository, NewAnnotation};
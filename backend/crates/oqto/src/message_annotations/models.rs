@@ -0,0 +1,89 @@
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+
+/// What an annotation says about a message.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AnnotationKind {
+    /// `up` or `down`; one per user and message.
+    Reaction,
+    /// A named problem such as `hallucination`; each once per user.
+    Flag,
+    /// Free text.
+    Note,
+}
+
+impl AnnotationKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AnnotationKind::Reaction => "reaction",
+            AnnotationKind::Flag => "flag",
+            AnnotationKind::Note => "note",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "reaction" => Some(AnnotationKind::Reaction),
+            "flag" => Some(AnnotationKind::Flag),
+            "note" => Some(AnnotationKind::Note),
+            _ => None,
+        }
+    }
+}
+
+/// A reaction, flag or note on an agent response.
+#[derive(Debug, Clone, Serialize)]
+pub struct MessageAnnotation {
+    pub id: String,
+    pub session_id: String,
+    pub message_id: String,
+    /// Who annotated.
+    pub user_id: String,
+    /// Shared workspace of the session; None for personal sessions.
+    pub workspace_id: Option<String>,
+    pub kind: AnnotationKind,
+    /// `up`/`down`, the flag name, or the note text.
+    pub value: String,
+    /// Provider and model that produced the message.
+    pub provider: Option<String>,
+    pub model: Option<String>,
+    pub created_at: String,
+    pub updated_at: String,
+}
+
+/// Request body for annotating a message.
+#[derive(Debug, Clone, Deserialize)]
+pub struct CreateAnnotationRequest {
+    pub kind: AnnotationKind,
+    pub value: String,
+}
+
+/// Filters of the feedback statistics.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct FeedbackStatsQuery {
+    /// First day to include (YYYY-MM-DD, inclusive).
+    pub since: Option<String>,
+    /// Last day to include (YYYY-MM-DD, inclusive).
+    pub until: Option<String>,
+    /// Restrict to one user (admin endpoint only).
+    pub user_id: Option<String>,
+    pub provider: Option<String>,
+}
+
+/// Feedback on the responses of one model.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ModelFeedbackStat {
+    pub provider: Option<String>,
+    pub model: Option<String>,
+    /// Responses with at least one annotation.
+    pub messages: i64,
+    pub thumbs_up: i64,
+    pub thumbs_down: i64,
+    /// Share of reactions that are thumbs up; None without reactions.
+    pub approval: Option<f64>,
+    /// Flags by name.
+    pub flags: BTreeMap<String, i64>,
+    pub notes: i64,
+}
@@ -0,0 +1,459 @@
+use std::collections::BTreeMap;
+
+use anyhow::{Context, Result};
+use sqlx::FromRow;
+
+use crate::db::{self, DbPool, on_pool};
+
+use super::{AnnotationKind, FeedbackStatsQuery, MessageAnnotation, ModelFeedbackStat};
+
+const ANNOTATION_COLUMNS: &str = "id, session_id, message_id, user_id, workspace_id, kind, value, \
+     provider, model, created_at, updated_at";
+
+#[derive(Debug, Clone, FromRow)]
+struct AnnotationRow {
+    id: String,
+    session_id: String,
+    message_id: String,
+    user_id: String,
+    workspace_id: Option<String>,
+    kind: String,
+    value: String,
+    provider: Option<String>,
+    model: Option<String>,
+    created_at: String,
+    updated_at: String,
+}
+
+impl From<AnnotationRow> for MessageAnnotation {
+    fn from(row: AnnotationRow) -> Self {
+        Self {
+            id: row.id,
+            session_id: row.session_id,
+            message_id: row.message_id,
+            user_id: row.user_id,
+            workspace_id: row.workspace_id,
+            // The column is constrained to the known values.
+            kind: AnnotationKind::parse(&row.kind).unwrap_or(AnnotationKind::Note),
+            value: row.value,
+            provider: row.provider,
+            model: row.model,
+            created_at: row.created_at,
+            updated_at: row.updated_at,
+        }
+    }
+}
+
+#[derive(Debug, Clone, FromRow)]
+struct ModelStatRow {
+    provider: Option<String>,
+    model: Option<String>,
+    messages: i64,
+    thumbs_up: i64,
+    thumbs_down: i64,
+    notes: i64,
+}
+
+#[derive(Debug, Clone, FromRow)]
+struct FlagStatRow {
+    provider: Option<String>,
+    model: Option<String>,
+    value: String,
+    count: i64,
+}
+
+/// A new annotation and the message it is on.
+#[derive(Debug, Clone)]
+pub struct NewAnnotation<'a> {
+    pub session_id: &'a str,
+    pub message_id: &'a str,
+    pub user_id: &'a str,
+    pub workspace_id: Option<&'a str>,
+    pub kind: AnnotationKind,
+    pub value: &'a str,
+    pub provider: Option<&'a str>,
+    pub model: Option<&'a str>,
+}
+
+/// `WHERE` clause of the feedback statistics and the values it binds.
+fn stats_filter(query: &FeedbackStatsQuery, user_id: Option<&str>) -> (String, Vec<String>) {
+    let mut sql = String::from(" WHERE 1 = 1");
+    let mut binds = Vec::new();
+    let mut push = |clause: &str, value: &str| {
+        binds.push(value.to_string());
+        sql.push_str(&format!(" AND {clause} ${}", binds.len()));
+    };
+    if let Some(user_id) = user_id.or(query.user_id.as_deref()) {
+        push("user_id =", user_id);
+    }
+    if let Some(provider) = query.provider.as_deref() {
+        push("provider =", provider);
+    }
+    if let Some(since) = query.since.as_deref() {
+        push("substr(created_at, 1, 10) >=", since);
+    }
+    if let Some(until) = query.until.as_deref() {
+        push("substr(created_at, 1, 10) <=", until);
+    }
+    (sql, binds)
+}
+
+#[derive(Debug, Clone)]
+pub struct AnnotationRepository {
+    pool: DbPool,
+}
+
+impl AnnotationRepository {
+    pub fn new(pool: DbPool) -> Self {
+        Self { pool }
+    }
+
+    fn generate_id() -> String {
+        format!("ann_{}", nanoid::nanoid!(12))
+    }
+
+    /// Annotate a message. A user's reaction replaces their previous one;
+    /// repeating a flag returns the existing one.
+    pub async fn create(&self, annotation: &NewAnnotation<'_>) -> Result<MessageAnnotation> {
+        let id = Self::generate_id();
+        let conflict = match annotation.kind {
+            AnnotationKind::Reaction => {
+                " ON CONFLICT (session_id, message_id, user_id) WHERE kind = 'reaction' \
+                 DO UPDATE SET value = excluded.value, updated_at = excluded.updated_at"
+            }
+            AnnotationKind::Flag => {
+                " ON CONFLICT (session_id, message_id, user_id, value) WHERE kind = 'flag' \
+                 DO UPDATE SET updated_at = excluded.updated_at"
+            }
+            AnnotationKind::Note => "",
+        };
+        let sql = format!(
+            "INSERT INTO message_annotations \
+                 (id, session_id, message_id, user_id, workspace_id, kind, value, provider, model, \
+                  created_at, updated_at) \
+             VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $10){conflict}"
+        );
+        on_pool!(&self.pool, |pool| sqlx::query(&sql)
+            .bind(&id)
+            .bind(annotation.session_id)
+            .bind(annotation.message_id)
+            .bind(annotation.user_id)
+            .bind(annotation.workspace_id)
+            .bind(annotation.kind.as_str())
+            .bind(annotation.value)
+            .bind(annotation.provider)
+            .bind(annotation.model)
+            .bind(db::now())
+            .execute(pool)
+            .await)
+        .context("insert message annotation")?;
+
+        let stored = match annotation.kind {
+            AnnotationKind::Note => self.get(&id).await?,
+            kind => {
+                let sql = format!(
+                    "SELECT {ANNOTATION_COLUMNS} FROM message_annotations \
+                     WHERE session_id = $1 AND message_id = $2 AND user_id = $3 AND kind = $4 \
+                     AND (kind = 'reaction' OR value = $5)"
+                );
+                let row = on_pool!(&self.pool, |pool| sqlx::query_as::<_, AnnotationRow>(&sql)
+                    .bind(annotation.session_id)
+                    .bind(annotation.message_id)
+                    .bind(annotation.user_id)
+                    .bind(kind.as_str())
+                    .bind(annotation.value)
+                    .fetch_optional(pool)
+                    .await)
+                .context("get message annotation")?;
+                row.map(Into::into)
+            }
+        };
+        stored.ok_or_else(|| anyhow::anyhow!("Annotation not found after creation"))
+    }
+
+    pub async fn get(&self, id: &str) -> Result<Option<MessageAnnotation>> {
+        let sql = format!("SELECT {ANNOTATION_COLUMNS} FROM message_annotations WHERE id = $1");
+        let row = on_pool!(&self.pool, |pool| sqlx::query_as::<_, AnnotationRow>(&sql)
+            .bind(id)
+            .fetch_optional(pool)
+            .await)
+        .context("get message annotation")?;
+        Ok(row.map(Into::into))
+    }
+
+    /// Annotations `viewer` can see in a session, oldest first: all of the
+    /// shared ones and the viewer's own.
+    pub async fn list_for_session(
+        &self,
+        session_id: &str,
+        viewer: &str,
+    ) -> Result<Vec<MessageAnnotation>> {
+        let sql = format!(
+            "SELECT {ANNOTATION_COLUMNS} FROM message_annotations \
+             WHERE session_id = $1 AND (workspace_id IS NOT NULL OR user_id = $2) \
+             ORDER BY created_at, id"
+        );
+        let rows = on_pool!(&self.pool, |pool| sqlx::query_as::<_, AnnotationRow>(&sql)
+            .bind(session_id)
+            .bind(viewer)
+            .fetch_all(pool)
+            .await)
+        .context("list message annotations")?;
+        Ok(rows.into_iter().map(Into::into).collect())
+    }
+
+    pub async fn count_for_session(&self, session_id: &str) -> Result<i64> {
+        on_pool!(&self.pool, |pool| sqlx::query_scalar(
+            "SELECT COUNT(*) FROM message_annotations WHERE session_id = $1"
+        )
+        .bind(session_id)
+        .fetch_one(pool)
+        .await)
+        .context("count message annotations")
+    }
+
+    pub async fn delete(&self, id: &str) -> Result<bool> {
+        let deleted = on_pool!(&self.pool, |pool| sqlx::query(
+            "DELETE FROM message_annotations WHERE id = $1"
+        )
+        .bind(id)
+        .execute(pool)
+        .await
+        .map(|r| r.rows_affected()))
+        .context("delete message annotation")?;
+        Ok(deleted > 0)
+    }
+
+    /// Feedback per provider and model, most annotated first. `user_id`
+    /// restricts it to one user's annotations.
+    pub async fn stats(
+        &self,
+        query: &FeedbackStatsQuery,
+        user_id: Option<&str>,
+    ) -> Result<Vec<ModelFeedbackStat>> {
+        let (filter, binds) = stats_filter(query, user_id);
+        let sql = format!(
+            "SELECT provider, model, \
+                 COUNT(DISTINCT session_id || ':' || message_id) AS messages, \
+                 SUM(CASE WHEN kind = 'reaction' AND value = 'up' THEN 1 ELSE 0 END) AS thumbs_up, \
+                 SUM(CASE WHEN kind = 'reaction' AND value = 'down' THEN 1 ELSE 0 END) AS thumbs_down, \
+                 SUM(CASE WHEN kind = 'note' THEN 1 ELSE 0 END) AS notes \
+             FROM message_annotations{filter} \
+             GROUP BY provider, model ORDER BY messages DESC, provider, model"
+        );
+        let rows = on_pool!(&self.pool, |pool| {
+            let mut q = sqlx::query_as::<_, ModelStatRow>(&sql);
+            for value in &binds {
+                q = q.bind(value);
+            }
+            q.fetch_all(pool).await
+        })
+        .context("query model feedback stats")?;
+
+        let sql = format!(
+            "SELECT provider, model, value, COUNT(*) AS count \
+             FROM message_annotations{filter} AND kind = 'flag' \
+             GROUP BY provider, model, value"
+        );
+        let flag_rows = on_pool!(&self.pool, |pool| {
+            let mut q = sqlx::query_as::<_, FlagStatRow>(&sql);
+            for value in &binds {
+                q = q.bind(value);
+            }
+            q.fetch_all(pool).await
+        })
+        .context("query flag stats")?;
+        let mut flags: BTreeMap<(Option<String>, Option<String>), BTreeMap<String, i64>> =
+            BTreeMap::new();
+        for row in flag_rows {
+            flags
+                .entry((row.provider, row.model))
+                .or_default()
+                .insert(row.value, row.count);
+        }
+
+        Ok(rows
+            .into_iter()
+            .map(|row| {
+                let reactions = row.thumbs_up + row.thumbs_down;
+                ModelFeedbackStat {
+                    flags: flags
+                        .remove(&(row.provider.clone(), row.model.clone()))
+                        .unwrap_or_default(),
+                    provider: row.provider,
+                    model: row.model,
+                    messages: row.messages,
+                    thumbs_up: row.thumbs_up,
+                    thumbs_down: row.thumbs_down,
+                    approval: (reactions > 0).then(|| row.thumbs_up as f64 / reactions as f64),
+                    notes: row.notes,
+                }
+            })
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::Database;
+
+    async fn repo() -> AnnotationRepository {
+        let db = Database::in_memory().await.unwrap();
+        for user in ["alice", "bob"] {
+            sqlx::query(
+                "INSERT INTO users (id, username, email, display_name) VALUES (?, ?, ?, ?)",
+            )
+            .bind(user)
+            .bind(user)
+            .bind(format!("{user}@example.com"))
+            .bind(user)
+            .execute(db.pool())
+            .await
+            .unwrap();
+        }
+        AnnotationRepository::new(db.shared().clone())
+    }
+
+    fn annotation<'a>(
+        user_id: &'a str,
+        message_id: &'a str,
+        kind: AnnotationKind,
+        value: &'a str,
+    ) -> NewAnnotation<'a> {
+        NewAnnotation {
+            session_id: "ses_1",
+            message_id,
+            user_id,
+            workspace_id: None,
+            kind,
+            value,
+            provider: Some("anthropic"),
+            model: Some("claude-sonnet"),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_reactions_and_flags_are_unique_per_user() {
+        let repo = repo().await;
+        let up = repo
+            .create(&annotation("alice", "m1", AnnotationKind::Reaction, "up"))
+            .await
+            .unwrap();
+        let down = repo
+            .create(&annotation("alice", "m1", AnnotationKind::Reaction, "down"))
+            .await
+            .unwrap();
+        assert_eq!(down.id, up.id);
+        assert_eq!(down.value, "down");
+
+        let flag = repo
+            .create(&annotation(
+                "alice",
+                "m1",
+                AnnotationKind::Flag,
+                "hallucination",
+            ))
+            .await
+            .unwrap();
+        let again = repo
+            .create(&annotation(
+                "alice",
+                "m1",
+                AnnotationKind::Flag,
+                "hallucination",
+            ))
+            .await
+            .unwrap();
+        assert_eq!(again.id, flag.id);
+        repo.create(&annotation(
+            "alice",
+            "m1",
+            AnnotationKind::Note,
+            "wrong API",
+        ))
+        .await
+        .unwrap();
+        repo.create(&annotation(
+            "alice",
+            "m1",
+            AnnotationKind::Note,
+            "wrong API",
+        ))
+        .await
+        .unwrap();
+        assert_eq!(repo.count_for_session("ses_1").await.unwrap(), 4);
+
+        // Personal annotations are private to their author.
+        assert_eq!(
+            repo.list_for_session("ses_1", "alice").await.unwrap().len(),
+            4
+        );
+        assert!(
+            repo.list_for_session("ses_1", "bob")
+                .await
+                .unwrap()
+                .is_empty()
+        );
+
+        assert!(repo.delete(&up.id).await.unwrap());
+        assert!(repo.get(&up.id).await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_feedback_stats_per_model() {
+        let repo = repo().await;
+        repo.create(&annotation("alice", "m1", AnnotationKind::Reaction, "up"))
+            .await
+            .unwrap();
+        repo.create(&annotation("bob", "m1", AnnotationKind::Reaction, "down"))
+            .await
+            .unwrap();
+        repo.create(&annotation("bob", "m2", AnnotationKind::Reaction, "up"))
+            .await
+            .unwrap();
+        repo.create(&annotation(
+            "bob",
+            "m2",
+            AnnotationKind::Flag,
+            "hallucination",
+        ))
+        .await
+        .unwrap();
+        repo.create(&NewAnnotation {
+            provider: Some("openai"),
+            model: Some("gpt"),
+            ..annotation("bob", "m3", AnnotationKind::Note, "too verbose")
+        })
+        .await
+        .unwrap();
+
+        let stats = repo
+            .stats(&FeedbackStatsQuery::default(), None)
+            .await
+            .unwrap();
+        assert_eq!(stats.len(), 2);
+        let sonnet = &stats[0];
+        assert_eq!(sonnet.model.as_deref(), Some("claude-sonnet"));
+        assert_eq!(sonnet.messages, 2);
+        assert_eq!((sonnet.thumbs_up, sonnet.thumbs_down), (2, 1));
+        assert_eq!(sonnet.approval, Some(2.0 / 3.0));
+        assert_eq!(sonnet.flags["hallucination"], 1);
+        assert_eq!(stats[1].notes, 1);
+        assert_eq!(stats[1].approval, None);
+
+        let alice = repo
+            .stats(&FeedbackStatsQuery::default(), Some("alice"))
+            .await
+            .unwrap();
+        assert_eq!(alice.len(), 1);
+        assert_eq!(alice[0].thumbs_up, 1);
+        assert!(alice[0].flags.is_empty());
+
+        let future = FeedbackStatsQuery {
+            since: Some("2999-01-01".to_string()),
+            ..Default::default()
+        };
+        assert!(repo.stats(&future, None).await.unwrap().is_empty());
+    }
+}
@@ -0,0 +1,247 @@
+//! Listener setup and address formatting that works the same over IPv4 and
+//! IPv6.
+//!
+//! `oqto serve` binds one socket. On an IPv6 wildcard (`::`) it is made
+//! dual-stack by default, so IPv4 clients arrive as v4-mapped addresses
+//! (`::ffff:a.b.c.d`); [`canonical_peer`] turns those back into IPv4 before
+//! handlers see them, keeping loopback checks, rate limits and audit
+//! entries consistent across both kinds of bind.
+//!
+//! Behind a reverse proxy the peer is the proxy. [`resolve_client_ip`]
+//! records the address the proxy forwarded for as [`ClientIp`] when the
+//! peer is one of `[server] trusted_proxies`; rate limits, registration
+//! limits and trigger logs all read it through [`client_ip`].
+
+use std::borrow::Cow;
+use std::net::{IpAddr, Ipv6Addr, SocketAddr};
+use std::sync::Arc;
+
+use anyhow::{Context, Result};
+use axum::extract::{ConnectInfo, Request, State};
+use axum::http::{Extensions, HeaderMap};
+use axum::middleware::Next;
+use axum::response::Response;
+use socket2::{Domain, Socket, Type};
+use tokio::net::TcpListener;
+
+/// Pending connections queued by the kernel.
+const LISTEN_BACKLOG: i32 = 1024;
+
+/// Parse a bind host (`0.0.0.0`, `::`, `[::]`, `fe80::1`...) and port.
+pub fn bind_addr(host: &str, port: u16) -> Result<SocketAddr> {
+    let host = host.trim();
+    let ip: IpAddr = host
+        .strip_prefix('[')
+        .and_then(|h| h.strip_suffix(']'))
+        .unwrap_or(host)
+        .parse()
+        .with_context(|| format!("invalid bind address {host:?}"))?;
+    Ok(SocketAddr::new(ip, port))
+}
+
+/// Bind a listener on `addr`. An IPv6 socket also accepts IPv4 connections
+/// when `dual_stack` is set, whatever the system default is.
+pub fn bind(addr: SocketAddr, dual_stack: bool) -> std::io::Result<TcpListener> {
+    let socket = Socket::new(Domain::for_address(addr), Type::STREAM, None)?;
+    if addr.is_ipv6() {
+        socket.set_only_v6(!dual_stack)?;
+    }
+    #[cfg(unix)]
+    socket.set_reuse_address(true)?;
+    socket.set_nonblocking(true)?;
+    socket.bind(&addr.into())?;
+    socket.listen(LISTEN_BACKLOG)?;
+    TcpListener::from_std(socket.into())
+}
+
+/// `ip` with v4-mapped IPv6 addresses turned into IPv4.
+pub fn canonical_ip(ip: IpAddr) -> IpAddr {
+    ip.to_canonical()
+}
+
+/// Middleware rewriting the peer address of a request to its canonical
+/// form (see [`canonical_ip`]).
+pub async fn canonical_peer(mut req: Request, next: Next) -> Response {
+    if let Some(ConnectInfo(addr)) = req.extensions_mut().get_mut::<ConnectInfo<SocketAddr>>() {
+        addr.set_ip(canonical_ip(addr.ip()));
+    }
+    next.run(req).await
+}
+
+/// Client address of a request, as set by [`resolve_client_ip`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ClientIp(pub IpAddr);
+
+/// Peers whose `X-Forwarded-For` is believed (`[server] trusted_proxies`).
+#[derive(Debug, Clone, Default)]
+pub struct TrustedProxies(Arc<Vec<IpAddr>>);
+
+impl TrustedProxies {
+    pub fn new(proxies: impl IntoIterator<Item = IpAddr>) -> Self {
+        Self(Arc::new(proxies.into_iter().map(canonical_ip).collect()))
+    }
+
+    fn trusts(&self, ip: IpAddr) -> bool {
+        self.0.contains(&canonical_ip(ip))
+    }
+
+    /// Client address of a request from `peer`. When `peer` is a trusted
+    /// proxy, `X-Forwarded-For` is read from the right, skipping further
+    /// trusted proxies; the first other hop is the client.
+    pub fn client_ip(&self, peer: IpAddr, headers: &HeaderMap) -> IpAddr {
+        if !self.trusts(peer) {
+            return peer;
+        }
+        let hops = headers
+            .get_all("x-forwarded-for")
+            .iter()
+            .filter_map(|v| v.to_str().ok())
+            .flat_map(|v| v.split(','))
+            .filter_map(|hop| hop.trim().parse::<IpAddr>().ok())
+            .map(canonical_ip)
+            .collect::<Vec<_>>();
+        let mut client = peer;
+        for hop in hops.into_iter().rev() {
+            client = hop;
+            if !self.trusts(hop) {
+                break;
+            }
+        }
+        client
+    }
+}
+
+/// Middleware recording the client address of a request as [`ClientIp`].
+/// Runs after [`canonical_peer`].
+pub async fn resolve_client_ip(
+    State(proxies): State<TrustedProxies>,
+    mut req: Request,
+    next: Next,
+) -> Response {
+    if let Some(ConnectInfo(addr)) = req.extensions().get::<ConnectInfo<SocketAddr>>() {
+        let ip = proxies.client_ip(addr.ip(), req.headers());
+        req.extensions_mut().insert(ClientIp(ip));
+    }
+    next.run(req).await
+}
+
+/// Client address of a request: the one [`resolve_client_ip`] recorded,
+/// else the peer. None for requests without a peer address, like those
+/// on the admin socket.
+pub fn client_ip(extensions: &Extensions) -> Option<IpAddr> {
+    extensions
+        .get::<ClientIp>()
+        .map(|ClientIp(ip)| *ip)
+        .or_else(|| {
+            extensions
+                .get::<ConnectInfo<SocketAddr>>()
+                .map(|ConnectInfo(addr)| addr.ip())
+        })
+}
+
+/// `host` as it appears in a URL authority: IPv6 literals are bracketed,
+/// with the zone of a link-local address percent-encoded (RFC 6874).
+pub fn url_host(host: &str) -> Cow<'_, str> {
+    let (addr, zone) = match host.split_once('%') {
+        Some((addr, zone)) => (addr, Some(zone)),
+        None => (host, None),
+    };
+    if addr.parse::<Ipv6Addr>().is_err() {
+        return Cow::Borrowed(host);
+    }
+    match zone {
+        Some(zone) => Cow::Owned(format!("[{addr}%25{zone}]")),
+        None => Cow::Owned(format!("[{addr}]")),
+    }
+}
+
+/// `host:port` for a URL, bracketing IPv6 literals.
+pub fn authority(host: &str, port: u16) -> String {
+    format!("{}:{port}", url_host(host))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bind_addr() {
+        assert_eq!(
+            bind_addr("0.0.0.0", 8080).unwrap(),
+            "0.0.0.0:8080".parse::<SocketAddr>().unwrap()
+        );
+        assert_eq!(
+            bind_addr("[::]", 8080).unwrap(),
+            "[::]:8080".parse::<SocketAddr>().unwrap()
+        );
+        assert_eq!(
+            bind_addr("::", 8080).unwrap(),
+            bind_addr("[::]", 8080).unwrap()
+        );
+        assert!(bind_addr("example.com", 8080).is_err());
+    }
+
+    #[test]
+    fn test_url_hosts() {
+        assert_eq!(authority("localhost", 80), "localhost:80");
+        assert_eq!(authority("10.0.0.1", 80), "10.0.0.1:80");
+        assert_eq!(authority("::1", 80), "[::1]:80");
+        assert_eq!(authority("fe80::1%eth0", 80), "[fe80::1%25eth0]:80");
+
+        let mapped: IpAddr = "::ffff:127.0.0.1".parse().unwrap();
+        assert!(canonical_ip(mapped).is_loopback());
+        assert!(canonical_ip(mapped).is_ipv4());
+    }
+
+    #[test]
+    fn test_client_ip_behind_trusted_proxies() {
+        let ip = |s: &str| s.parse::<IpAddr>().unwrap();
+        let forwarded = |value: &str| {
+            let mut headers = HeaderMap::new();
+            headers.insert("x-forwarded-for", value.parse().unwrap());
+            headers
+        };
+        let proxies = TrustedProxies::new([ip("127.0.0.1"), ip("10.0.0.2")]);
+
+        // Untrusted peers cannot claim another address.
+        let spoofed = forwarded("203.0.113.7");
+        assert_eq!(
+            proxies.client_ip(ip("198.51.100.1"), &spoofed),
+            ip("198.51.100.1")
+        );
+        // The hop the proxy appended wins over what the client sent.
+        let headers = forwarded("203.0.113.7, 198.51.100.1");
+        assert_eq!(
+            proxies.client_ip(ip("::ffff:127.0.0.1"), &headers),
+            ip("198.51.100.1")
+        );
+        // Chained trusted proxies are skipped.
+        let headers = forwarded("198.51.100.1, 10.0.0.2");
+        assert_eq!(
+            proxies.client_ip(ip("127.0.0.1"), &headers),
+            ip("198.51.100.1")
+        );
+        assert_eq!(
+            proxies.client_ip(ip("127.0.0.1"), &HeaderMap::new()),
+            ip("127.0.0.1")
+        );
+        assert_eq!(
+            TrustedProxies::default().client_ip(ip("127.0.0.1"), &spoofed),
+            ip("127.0.0.1")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_dual_stack_listener_accepts_ipv4() {
+        let Ok(listener) = bind(bind_addr("::", 0).unwrap(), true) else {
+            // IPv6 is disabled in this environment.
+            return;
+        };
+        let port = listener.local_addr().unwrap().port();
+        let connect = tokio::net::TcpStream::connect(("127.0.0.1", port));
+        let (accepted, connected) = tokio::join!(listener.accept(), connect);
+        let (_, peer) = accepted.unwrap();
+        connected.unwrap();
+        assert_eq!(canonical_ip(peer.ip()), IpAddr::from([127, 0, 0, 1]));
+    }
+}
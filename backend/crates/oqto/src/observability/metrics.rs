@@ -0,0 +1,455 @@
+//! Prometheus metrics.
+//!
+//! Counters and histograms live in a process-wide [`Metrics`] registry that
+//! request paths update directly; values that already exist elsewhere
+//! (running sessions, EAVS spend) are read when `/api/metrics` is scraped.
+//! Runner RPCs and hstry writes are reported through the observer hooks of
+//! `oqto-runner` and `oqto-history` installed by [`install_observers`].
+
+use std::fmt::Write as _;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicI64, AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+use dashmap::DashMap;
+use dashmap::mapref::multiple::RefMulti;
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use tokio::sync::Mutex;
+use tracing::debug;
+
+use crate::eavs::EavsClient;
+
+/// Upper bounds of the latency histogram buckets, in seconds.
+const BUCKETS: [f64; 11] = [
+    0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0,
+];
+
+/// Prefix of the EAVS keys provisioned for platform users.
+const EAVS_USER_KEY_PREFIX: &str = "oqto-user-";
+
+/// Metrics endpoint configuration.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MetricsConfig {
+    /// Serve `/api/metrics`.
+    pub enabled: bool,
+    /// Bearer token scrapers must send. The endpoint is not served while it
+    /// is empty.
+    pub bearer_token: String,
+    /// Seconds per-user EAVS spend is cached between scrapes.
+    pub eavs_refresh_secs: u64,
+}
+
+impl Default for MetricsConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            bearer_token: String::new(),
+            eavs_refresh_secs: 60,
+        }
+    }
+}
+
+/// Latency histogram with fixed [`BUCKETS`].
+#[derive(Default)]
+struct Histogram {
+    buckets: [AtomicU64; BUCKETS.len()],
+    sum_micros: AtomicU64,
+    count: AtomicU64,
+}
+
+impl Histogram {
+    fn observe(&self, elapsed: Duration) {
+        let secs = elapsed.as_secs_f64();
+        if let Some(i) = BUCKETS.iter().position(|bound| secs <= *bound) {
+            self.buckets[i].fetch_add(1, Ordering::Relaxed);
+        }
+        self.sum_micros
+            .fetch_add(elapsed.as_micros() as u64, Ordering::Relaxed);
+        self.count.fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn render(&self, out: &mut String, name: &str, label: &str, value: &str) {
+        let value = escape_label(value);
+        let mut cumulative = 0;
+        for (bound, bucket) in BUCKETS.iter().zip(&self.buckets) {
+            cumulative += bucket.load(Ordering::Relaxed);
+            let _ = writeln!(
+                out,
+                "{name}_bucket{{{label}=\"{value}\",le=\"{bound}\"}} {cumulative}"
+            );
+        }
+        let count = self.count.load(Ordering::Relaxed);
+        let sum = self.sum_micros.load(Ordering::Relaxed) as f64 / 1_000_000.0;
+        let _ = writeln!(
+            out,
+            "{name}_bucket{{{label}=\"{value}\",le=\"+Inf\"}} {count}"
+        );
+        let _ = writeln!(out, "{name}_sum{{{label}=\"{value}\"}} {sum}");
+        let _ = writeln!(out, "{name}_count{{{label}=\"{value}\"}} {count}");
+    }
+}
+
+/// Calls and failures of the instrumented dependencies since startup.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct CallTotals {
+    pub runner_rpcs: u64,
+    pub runner_rpc_errors: u64,
+    pub hstry_writes: u64,
+    pub hstry_write_errors: u64,
+}
+
+/// Process-wide metric registry.
+#[derive(Default)]
+pub struct Metrics {
+    container_starts: AtomicU64,
+    container_stops: AtomicU64,
+    ws_connections: AtomicI64,
+    hstry_writes: DashMap<String, Histogram>,
+    hstry_write_errors: DashMap<String, AtomicU64>,
+    runner_rpcs: DashMap<String, Histogram>,
+    runner_rpc_errors: DashMap<String, AtomicU64>,
+}
+
+static METRICS: Lazy<Metrics> = Lazy::new(Metrics::default);
+
+/// The process-wide registry.
+pub fn metrics() -> &'static Metrics {
+    &METRICS
+}
+
+/// Report runner RPCs and hstry writes to the registry.
+pub fn install_observers() {
+    oqto_runner::client::set_request_observer(|method, elapsed, ok| {
+        metrics().observe_runner_rpc(method, elapsed, ok)
+    });
+    crate::history::hstry::set_write_observer(|op, elapsed, ok| {
+        metrics().observe_hstry_write(op, elapsed, ok)
+    });
+}
+
+/// Decrements the WebSocket gauge when dropped.
+pub struct WsConnectionGuard<'a>(&'a AtomicI64);
+
+impl Drop for WsConnectionGuard<'_> {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+impl Metrics {
+    pub fn container_started(&self) {
+        self.container_starts.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn container_stopped(&self) {
+        self.container_stops.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Count an open WebSocket until the returned guard is dropped.
+    pub fn ws_connected(&self) -> WsConnectionGuard<'_> {
+        self.ws_connections.fetch_add(1, Ordering::Relaxed);
+        WsConnectionGuard(&self.ws_connections)
+    }
+
+    pub fn observe_runner_rpc(&self, method: &str, elapsed: Duration, ok: bool) {
+        observe(
+            &self.runner_rpcs,
+            &self.runner_rpc_errors,
+            method,
+            elapsed,
+            ok,
+        );
+    }
+
+    pub fn observe_hstry_write(&self, op: &str, elapsed: Duration, ok: bool) {
+        observe(
+            &self.hstry_writes,
+            &self.hstry_write_errors,
+            op,
+            elapsed,
+            ok,
+        );
+    }
+
+    pub fn call_totals(&self) -> CallTotals {
+        let calls = |map: &DashMap<String, Histogram>| {
+            map.iter()
+                .map(|entry| entry.value().count.load(Ordering::Relaxed))
+                .sum()
+        };
+        let errors = |map: &DashMap<String, AtomicU64>| {
+            map.iter()
+                .map(|entry| entry.value().load(Ordering::Relaxed))
+                .sum()
+        };
+        CallTotals {
+            runner_rpcs: calls(&self.runner_rpcs),
+            runner_rpc_errors: errors(&self.runner_rpc_errors),
+            hstry_writes: calls(&self.hstry_writes),
+            hstry_write_errors: errors(&self.hstry_write_errors),
+        }
+    }
+
+    /// Render every metric in the Prometheus text exposition format.
+    pub fn render(&self, sessions_active: usize, eavs_spend: &[(String, f64)]) -> String {
+        let mut out = String::new();
+        header(
+            &mut out,
+            "oqto_sessions_active",
+            "gauge",
+            "Running sessions.",
+        );
+        let _ = writeln!(out, "oqto_sessions_active {sessions_active}");
+
+        header(
+            &mut out,
+            "oqto_container_starts_total",
+            "counter",
+            "Containers created or started.",
+        );
+        let _ = writeln!(
+            out,
+            "oqto_container_starts_total {}",
+            self.container_starts.load(Ordering::Relaxed)
+        );
+        header(
+            &mut out,
+            "oqto_container_stops_total",
+            "counter",
+            "Containers stopped.",
+        );
+        let _ = writeln!(
+            out,
+            "oqto_container_stops_total {}",
+            self.container_stops.load(Ordering::Relaxed)
+        );
+
+        header(
+            &mut out,
+            "oqto_ws_connections",
+            "gauge",
+            "Open WebSocket connections.",
+        );
+        let _ = writeln!(
+            out,
+            "oqto_ws_connections {}",
+            self.ws_connections.load(Ordering::Relaxed)
+        );
+
+        render_family(
+            &mut out,
+            "oqto_hstry_write_duration_seconds",
+            "oqto_hstry_write_errors_total",
+            "hstry write latency.",
+            "op",
+            &self.hstry_writes,
+            &self.hstry_write_errors,
+        );
+        render_family(
+            &mut out,
+            "oqto_runner_rpc_duration_seconds",
+            "oqto_runner_rpc_errors_total",
+            "Runner RPC latency, including retries.",
+            "method",
+            &self.runner_rpcs,
+            &self.runner_rpc_errors,
+        );
+
+        header(
+            &mut out,
+            "oqto_eavs_spend_usd",
+            "gauge",
+            "Total EAVS spend per user in USD.",
+        );
+        for (user, spend) in eavs_spend {
+            let _ = writeln!(
+                out,
+                "oqto_eavs_spend_usd{{user=\"{}\"}} {spend}",
+                escape_label(user)
+            );
+        }
+        out
+    }
+}
+
+fn observe(
+    histograms: &DashMap<String, Histogram>,
+    errors: &DashMap<String, AtomicU64>,
+    label: &str,
+    elapsed: Duration,
+    ok: bool,
+) {
+    histograms
+        .entry(label.to_string())
+        .or_default()
+        .observe(elapsed);
+    if !ok {
+        errors
+            .entry(label.to_string())
+            .or_default()
+            .fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+fn header(out: &mut String, name: &str, kind: &str, help: &str) {
+    let _ = writeln!(out, "# HELP {name} {help}");
+    let _ = writeln!(out, "# TYPE {name} {kind}");
+}
+
+fn render_family(
+    out: &mut String,
+    name: &str,
+    errors_name: &str,
+    help: &str,
+    label: &str,
+    histograms: &DashMap<String, Histogram>,
+    errors: &DashMap<String, AtomicU64>,
+) {
+    header(out, name, "histogram", help);
+    for entry in sorted(histograms) {
+        entry.value().render(out, name, label, entry.key());
+    }
+    header(out, errors_name, "counter", "Failed calls.");
+    for entry in sorted(errors) {
+        let _ = writeln!(
+            out,
+            "{errors_name}{{{label}=\"{}\"}} {}",
+            escape_label(entry.key()),
+            entry.value().load(Ordering::Relaxed)
+        );
+    }
+}
+
+/// Map entries ordered by key, so scrapes are stable.
+fn sorted<V>(map: &DashMap<String, V>) -> Vec<RefMulti<'_, String, V>> {
+    let mut entries: Vec<_> = map.iter().collect();
+    entries.sort_by(|a, b| a.key().cmp(b.key()));
+    entries
+}
+
+fn escape_label(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+/// Total spend per user from the EAVS keys provisioned for them.
+pub(crate) fn spend_by_user(keys: &[crate::eavs::KeyInfo]) -> Vec<(String, f64)> {
+    let mut spend: Vec<(String, f64)> = Vec::new();
+    for key in keys {
+        let Some(user) = key
+            .name
+            .as_deref()
+            .and_then(|name| name.strip_prefix(EAVS_USER_KEY_PREFIX))
+        else {
+            continue;
+        };
+        let user = user.strip_suffix("-oauth").unwrap_or(user);
+        match spend.iter_mut().find(|(existing, _)| existing == user) {
+            Some((_, total)) => *total += key.usage.total_spend_usd,
+            None => spend.push((user.to_string(), key.usage.total_spend_usd)),
+        }
+    }
+    spend.sort_by(|a, b| a.0.cmp(&b.0));
+    spend
+}
+
+/// Serves `/api/metrics`.
+pub struct MetricsService {
+    config: MetricsConfig,
+    eavs: Option<Arc<EavsClient>>,
+    spend_cache: Mutex<Option<(Instant, Vec<(String, f64)>)>>,
+}
+
+impl MetricsService {
+    pub fn new(config: MetricsConfig, eavs: Option<Arc<EavsClient>>) -> Self {
+        Self {
+            config,
+            eavs,
+            spend_cache: Mutex::new(None),
+        }
+    }
+
+    pub fn config(&self) -> &MetricsConfig {
+        &self.config
+    }
+
+    /// Per-user EAVS spend, refreshed at most every `eavs_refresh_secs`.
+    /// Keeps the last values when EAVS is unreachable.
+    async fn eavs_spend(&self) -> Vec<(String, f64)> {
+        let Some(eavs) = &self.eavs else {
+            return Vec::new();
+        };
+        let mut cache = self.spend_cache.lock().await;
+        let max_age = Duration::from_secs(self.config.eavs_refresh_secs);
+        if let Some((fetched, spend)) = cache.as_ref()
+            && fetched.elapsed() < max_age
+        {
+            return spend.clone();
+        }
+        match eavs.list_keys().await {
+            Ok(keys) => {
+                let spend = spend_by_user(&keys);
+                *cache = Some((Instant::now(), spend.clone()));
+                spend
+            }
+            Err(e) => {
+                debug!("Failed to list EAVS keys for metrics: {e}");
+                cache
+                    .as_ref()
+                    .map(|(_, spend)| spend.clone())
+                    .unwrap_or_default()
+            }
+        }
+    }
+
+    /// Render a scrape.
+    pub async fn render(&self, sessions_active: usize) -> String {
+        let spend = self.eavs_spend().await;
+        metrics().render(sessions_active, &spend)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_histogram_buckets_are_cumulative() {
+        let histogram = Histogram::default();
+        histogram.observe(Duration::from_millis(3));
+        histogram.observe(Duration::from_millis(40));
+        histogram.observe(Duration::from_secs(30));
+
+        let mut out = String::new();
+        histogram.render(&mut out, "x", "method", "ping");
+        assert!(out.contains("x_bucket{method=\"ping\",le=\"0.005\"} 1\n"));
+        assert!(out.contains("x_bucket{method=\"ping\",le=\"0.05\"} 2\n"));
+        assert!(out.contains("x_bucket{method=\"ping\",le=\"10\"} 2\n"));
+        assert!(out.contains("x_bucket{method=\"ping\",le=\"+Inf\"} 3\n"));
+        assert!(out.contains("x_count{method=\"ping\"} 3\n"));
+    }
+
+    #[test]
+    fn test_render_exposition() {
+        let metrics = Metrics::default();
+        metrics.container_started();
+        metrics.container_stopped();
+        let first = metrics.ws_connected();
+        let _second = metrics.ws_connected();
+        drop(first);
+        metrics.observe_runner_rpc("get_state", Duration::from_millis(20), false);
+
+        let out = metrics.render(4, &[("a\"b".to_string(), 1.5)]);
+        assert!(out.contains("# TYPE oqto_sessions_active gauge\noqto_sessions_active 4\n"));
+        assert!(out.contains("oqto_container_starts_total 1\n"));
+        assert!(out.contains("oqto_container_stops_total 1\n"));
+        assert!(out.contains("oqto_ws_connections 1\n"));
+        assert!(out.contains("oqto_runner_rpc_duration_seconds_count{method=\"get_state\"} 1\n"));
+        assert!(out.contains("oqto_runner_rpc_errors_total{method=\"get_state\"} 1\n"));
+        assert!(out.contains("oqto_eavs_spend_usd{user=\"a\\\"b\"} 1.5\n"));
+    }
+}
@@ -1,4 +1,6 @@
-//! Host observability helpers.
+//! Host observability helpers and Prometheus metrics.
+
+pub mod metrics;
 
 use anyhow::{Context, Result};
 use serde::Serialize;
@@ -0,0 +1,229 @@
+//! Admin-defined bootstrap script for a user's first workspace.
+//!
+//! The script runs once per user, through the user's runner (so as the
+//! user's Linux account), with the new workspace as working directory. Its
+//! combined stdout/stderr is captured into the onboarding record.
+
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use anyhow::{Context, Result, bail};
+use serde::{Deserialize, Serialize};
+
+/// Upper bound on the script size accepted from config.
+const MAX_SCRIPT_BYTES: usize = 64 * 1024;
+
+/// First-workspace bootstrap script configuration.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct BootstrapScriptConfig {
+    /// Run the script when a user's first workspace is created.
+    pub enabled: bool,
+    /// Inline script source. Mutually exclusive with `script_path`.
+    pub script: Option<String>,
+    /// Path to a script file read at startup. Mutually exclusive with `script`.
+    pub script_path: Option<PathBuf>,
+    /// Interpreter the script is passed to with `-c`.
+    pub shell: String,
+    /// Kill the script after this many seconds.
+    pub timeout_secs: u64,
+    /// Output kept in the onboarding record (the tail is kept when exceeded).
+    pub max_output_bytes: usize,
+    /// Run the script inside the runner's sandbox.
+    pub sandboxed: bool,
+}
+
+impl Default for BootstrapScriptConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            script: None,
+            script_path: None,
+            shell: "/bin/bash".to_string(),
+            timeout_secs: 600,
+            max_output_bytes: 64 * 1024,
+            sandboxed: false,
+        }
+    }
+}
+
+impl BootstrapScriptConfig {
+    /// Validate the configuration and load the script.
+    ///
+    /// Returns None when the feature is disabled.
+    pub fn load(&self) -> Result<Option<BootstrapScript>> {
+        if !self.enabled {
+            return Ok(None);
+        }
+
+        let source = match (&self.script, &self.script_path) {
+            (Some(_), Some(_)) => {
+                bail!("set either bootstrap_script.script or script_path, not both")
+            }
+            (None, None) => {
+                bail!("bootstrap_script is enabled but neither script nor script_path is set")
+            }
+            (Some(script), None) => script.clone(),
+            (None, Some(path)) => std::fs::read_to_string(path)
+                .with_context(|| format!("reading bootstrap script {}", path.display()))?,
+        };
+
+        if source.trim().is_empty() {
+            bail!("bootstrap script is empty");
+        }
+        if source.len() > MAX_SCRIPT_BYTES {
+            bail!(
+                "bootstrap script is {} bytes, limit is {MAX_SCRIPT_BYTES}",
+                source.len()
+            );
+        }
+        if source.contains('\0') {
+            bail!("bootstrap script contains NUL bytes");
+        }
+        if !Path::new(&self.shell).is_absolute() {
+            bail!("bootstrap_script.shell must be an absolute path");
+        }
+        if self.timeout_secs == 0 {
+            bail!("bootstrap_script.timeout_secs must be greater than zero");
+        }
+        check_syntax(&self.shell, &source)?;
+
+        Ok(Some(BootstrapScript {
+            source,
+            shell: self.shell.clone(),
+            timeout: Duration::from_secs(self.timeout_secs),
+            max_output_bytes: self.max_output_bytes,
+            sandboxed: self.sandboxed,
+        }))
+    }
+}
+
+/// Parse the script with `<shell> -n` so typos fail at startup rather than
+/// on every new user.
+fn check_syntax(shell: &str, source: &str) -> Result<()> {
+    let output = std::process::Command::new(shell)
+        .arg("-n")
+        .arg("-c")
+        .arg(source)
+        .output()
+        .with_context(|| format!("running {shell} -n to validate bootstrap script"))?;
+    if !output.status.success() {
+        bail!(
+            "bootstrap script failed syntax check: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    Ok(())
+}
+
+/// Validated bootstrap script ready to hand to a runner.
+#[derive(Debug, Clone)]
+pub struct BootstrapScript {
+    source: String,
+    shell: String,
+    timeout: Duration,
+    max_output_bytes: usize,
+    sandboxed: bool,
+}
+
+impl BootstrapScript {
+    /// Interpreter binary.
+    pub fn shell(&self) -> &str {
+        &self.shell
+    }
+
+    /// Interpreter arguments. stderr is folded into stdout so the runner's
+    /// stdout buffer captures both.
+    pub fn args(&self) -> Vec<String> {
+        vec!["-c".to_string(), format!("exec 2>&1\n{}", self.source)]
+    }
+
+    pub fn timeout(&self) -> Duration {
+        self.timeout
+    }
+
+    pub fn sandboxed(&self) -> bool {
+        self.sandboxed
+    }
+
+    /// Append `chunk` to `output`, keeping at most `max_output_bytes`.
+    /// Returns true if earlier output had to be dropped.
+    pub fn append_output(&self, output: &mut String, chunk: &str) -> bool {
+        output.push_str(chunk);
+        if output.len() <= self.max_output_bytes {
+            return false;
+        }
+        let mut cut = output.len() - self.max_output_bytes;
+        while !output.is_char_boundary(cut) {
+            cut += 1;
+        }
+        output.drain(..cut);
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(script: &str) -> BootstrapScriptConfig {
+        BootstrapScriptConfig {
+            enabled: true,
+            script: Some(script.to_string()),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn disabled_config_loads_nothing() {
+        assert!(BootstrapScriptConfig::default().load().unwrap().is_none());
+    }
+
+    #[test]
+    fn load_rejects_invalid_scripts() {
+        assert!(config("   ").load().is_err());
+        assert!(config("if then fi (").load().is_err());
+
+        let both = BootstrapScriptConfig {
+            script_path: Some(PathBuf::from("/tmp/x.sh")),
+            ..config("true")
+        };
+        assert!(both.load().is_err());
+    }
+
+    #[test]
+    fn load_reads_script_path() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("bootstrap.sh");
+        std::fs::write(&path, "git config --global init.defaultBranch main\n").unwrap();
+
+        let script = BootstrapScriptConfig {
+            enabled: true,
+            script_path: Some(path),
+            ..Default::default()
+        }
+        .load()
+        .unwrap()
+        .unwrap();
+        assert_eq!(
+            script.args()[1],
+            "exec 2>&1\ngit config --global init.defaultBranch main\n"
+        );
+    }
+
+    #[test]
+    fn append_output_keeps_tail() {
+        let script = BootstrapScriptConfig {
+            max_output_bytes: 8,
+            ..config("true")
+        }
+        .load()
+        .unwrap()
+        .unwrap();
+
+        let mut output = String::new();
+        assert!(!script.append_output(&mut output, "abcd"));
+        assert!(script.append_output(&mut output, "efghijkl"));
+        assert_eq!(output, "efghijkl");
+    }
+}
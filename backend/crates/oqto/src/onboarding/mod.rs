@@ -5,9 +5,12 @@
 //! - Component unlock tracking (sidebar, file tree, terminal, etc.)
 //! - User level detection (beginner, intermediate, technical)
 //! - Godmode for power users to skip onboarding
+//! - One-time admin bootstrap script for the first workspace
 
+mod bootstrap;
 mod models;
 mod service;
 
+pub use bootstrap::{BootstrapScript, BootstrapScriptConfig};
 pub use models::*;
 pub use service::OnboardingService;
@@ -231,6 +231,67 @@ impl UnlockedComponents {
     }
 }
 
+/// Outcome of a bootstrap script run.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BootstrapScriptStatus {
+    Running,
+    Succeeded,
+    Failed,
+}
+
+/// Record of the first-workspace bootstrap script run.
+///
+/// Its presence means the script has been attempted; it is never run again
+/// for the same user unless onboarding is reset.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BootstrapScriptRun {
+    pub status: BootstrapScriptStatus,
+    /// Workspace the script ran in.
+    pub workspace_path: String,
+    pub started_at: String,
+    #[serde(default)]
+    pub finished_at: Option<String>,
+    #[serde(default)]
+    pub exit_code: Option<i32>,
+    /// Combined stdout/stderr (tail only when truncated).
+    #[serde(default)]
+    pub output: String,
+    #[serde(default)]
+    pub output_truncated: bool,
+    /// Why the run failed without an exit code (runner unavailable, timeout).
+    #[serde(default)]
+    pub error: Option<String>,
+}
+
+impl BootstrapScriptRun {
+    /// Start a new run record.
+    pub fn started(workspace_path: impl Into<String>) -> Self {
+        Self {
+            status: BootstrapScriptStatus::Running,
+            workspace_path: workspace_path.into(),
+            started_at: chrono::Utc::now().to_rfc3339(),
+            finished_at: None,
+            exit_code: None,
+            output: String::new(),
+            output_truncated: false,
+            error: None,
+        }
+    }
+
+    /// Mark the run finished.
+    pub fn finish(&mut self, exit_code: Option<i32>, error: Option<String>) {
+        self.status = if exit_code == Some(0) && error.is_none() {
+            BootstrapScriptStatus::Succeeded
+        } else {
+            BootstrapScriptStatus::Failed
+        };
+        self.exit_code = exit_code;
+        self.error = error;
+        self.finished_at = Some(chrono::Utc::now().to_rfc3339());
+    }
+}
+
 /// Full onboarding state for a user.
 ///
 /// This is stored as JSON in the user's settings field.
@@ -275,6 +336,10 @@ pub struct OnboardingState {
     /// Tutorial step index (for resuming interrupted tutorials).
     #[serde(default)]
     pub tutorial_step: u32,
+
+    /// Result of the admin bootstrap script for the first workspace.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub bootstrap_script: Option<BootstrapScriptRun>,
 }
 
 impl OnboardingState {
@@ -1,10 +1,12 @@
 //! Onboarding service for managing user onboarding state.
 
 use anyhow::{Context, Result};
-use sqlx::SqlitePool;
 use tracing::{debug, instrument};
 
-use super::models::{OnboardingState, UnlockComponentRequest, UpdateOnboardingRequest};
+use super::models::{
+    BootstrapScriptRun, OnboardingState, UnlockComponentRequest, UpdateOnboardingRequest,
+};
+use crate::db::{self, DbPool, on_pool};
 
 /// Service for managing onboarding state.
 ///
@@ -13,7 +15,7 @@ use super::models::{OnboardingState, UnlockComponentRequest, UpdateOnboardingReq
 /// while keeping onboarding data with the user record.
 #[derive(Debug, Clone)]
 pub struct OnboardingService {
-    pool: SqlitePool,
+    pool: DbPool,
 }
 
 /// User settings wrapper that includes onboarding.
@@ -27,7 +29,7 @@ struct UserSettings {
 
 impl OnboardingService {
     /// Create a new onboarding service.
-    pub fn new(pool: SqlitePool) -> Self {
+    pub fn new(pool: DbPool) -> Self {
         Self { pool }
     }
 
@@ -36,12 +38,13 @@ impl OnboardingService {
     /// If the user has no onboarding state, returns a fresh state.
     #[instrument(skip(self))]
     pub async fn get(&self, user_id: &str) -> Result<OnboardingState> {
-        let row: Option<(Option<String>,)> =
-            sqlx::query_as("SELECT settings FROM users WHERE id = ?")
-                .bind(user_id)
-                .fetch_optional(&self.pool)
-                .await
-                .context("fetching user settings")?;
+        let row: Option<(Option<String>,)> = on_pool!(&self.pool, |pool| sqlx::query_as(
+            "SELECT settings FROM users WHERE id = $1"
+        )
+        .bind(user_id)
+        .fetch_optional(pool)
+        .await)
+        .context("fetching user settings")?;
 
         let Some((settings_json,)) = row else {
             // User doesn't exist - return fresh state
@@ -149,15 +152,48 @@ impl OnboardingService {
         Ok(state)
     }
 
+    /// Claim the one-time bootstrap script run for a user.
+    ///
+    /// Returns the new run record, or None if the script was already
+    /// attempted for this user.
+    #[instrument(skip(self))]
+    pub async fn begin_bootstrap_script(
+        &self,
+        user_id: &str,
+        workspace_path: &str,
+    ) -> Result<Option<BootstrapScriptRun>> {
+        let mut state = self.get(user_id).await?;
+        if state.bootstrap_script.is_some() {
+            return Ok(None);
+        }
+        let run = BootstrapScriptRun::started(workspace_path);
+        state.bootstrap_script = Some(run.clone());
+        self.save(user_id, &state).await?;
+        Ok(Some(run))
+    }
+
+    /// Store the outcome of a bootstrap script run.
+    #[instrument(skip(self, run))]
+    pub async fn record_bootstrap_script(
+        &self,
+        user_id: &str,
+        run: BootstrapScriptRun,
+    ) -> Result<()> {
+        let mut state = self.get(user_id).await?;
+        state.bootstrap_script = Some(run);
+        self.save(user_id, &state).await
+    }
+
     /// Save onboarding state to the user's settings.
     async fn save(&self, user_id: &str, state: &OnboardingState) -> Result<()> {
         // First, get existing settings
-        let row: Option<(Option<String>,)> =
-            sqlx::query_as("SELECT settings FROM users WHERE id = ?")
-                .bind(user_id)
-                .fetch_optional(&self.pool)
-                .await
-                .context("fetching user settings for save")?;
+        let row: Option<(Option<String>,)> = on_pool!(&self.pool, |pool| sqlx::query_as(
+            "SELECT settings FROM users WHERE id = $1"
+        )
+        .bind(user_id)
+        .fetch_optional(pool)
+        .await)
+        .context("fetching user settings for save")?;
 
         let Some(_) = row else {
             anyhow::bail!("User {} not found", user_id);
@@ -174,12 +210,16 @@ impl OnboardingService {
 
         let settings_json = serde_json::to_string(&settings).context("serializing settings")?;
 
-        sqlx::query("UPDATE users SET settings = ?, updated_at = datetime('now') WHERE id = ?")
-            .bind(&settings_json)
-            .bind(user_id)
-            .execute(&self.pool)
-            .await
-            .context("updating user settings")?;
+        on_pool!(&self.pool, |pool| sqlx::query(
+            "UPDATE users SET settings = $1, updated_at = $2 WHERE id = $3"
+        )
+        .bind(&settings_json)
+        .bind(db::now())
+        .bind(user_id)
+        .execute(pool)
+        .await
+        .map(|r| r.rows_affected()))
+        .context("updating user settings")?;
 
         debug!("Saved onboarding state for user {}", user_id);
         Ok(())
@@ -191,8 +231,8 @@ mod tests {
     use super::*;
     use crate::onboarding::{OnboardingStage, UserLevel};
 
-    async fn setup_test_db() -> SqlitePool {
-        let pool = SqlitePool::connect(":memory:").await.unwrap();
+    async fn setup_test_db() -> DbPool {
+        let pool = sqlx::SqlitePool::connect(":memory:").await.unwrap();
 
         sqlx::query(
             r#"
@@ -223,7 +263,7 @@ mod tests {
             .await
             .unwrap();
 
-        pool
+        DbPool::Sqlite(pool)
     }
 
     #[tokio::test]
@@ -327,6 +367,41 @@ mod tests {
         assert!(!state.needs_onboarding());
     }
 
+    #[tokio::test]
+    async fn test_bootstrap_script_runs_once() {
+        let pool = setup_test_db().await;
+        let service = OnboardingService::new(pool);
+
+        let mut run = service
+            .begin_bootstrap_script("test-user", "/home/test/oqto/main")
+            .await
+            .unwrap()
+            .expect("first claim succeeds");
+        assert!(
+            service
+                .begin_bootstrap_script("test-user", "/home/test/oqto/other")
+                .await
+                .unwrap()
+                .is_none()
+        );
+
+        run.output = "cloned starter repo\n".to_string();
+        run.finish(Some(1), None);
+        service
+            .record_bootstrap_script("test-user", run)
+            .await
+            .unwrap();
+
+        let loaded = service.get("test-user").await.unwrap();
+        let recorded = loaded.bootstrap_script.unwrap();
+        assert_eq!(
+            recorded.status,
+            crate::onboarding::BootstrapScriptStatus::Failed
+        );
+        assert_eq!(recorded.exit_code, Some(1));
+        assert_eq!(recorded.workspace_path, "/home/test/oqto/main");
+    }
+
     #[tokio::test]
     async fn test_reset() {
         let pool = setup_test_db().await;
@@ -0,0 +1,405 @@
+//! Outbox for messages agents want to send on the user's behalf.
+//!
+//! Agents cannot send email or Slack messages themselves. They stage a
+//! message with `oqtoctl outbox ...` (`POST /api/sessions/{id}/outbox`),
+//! which shows up as a pending item with its full content in the user's
+//! notifications. The user approves it, optionally after editing, or
+//! rejects it; only then does the backend deliver it with credentials the
+//! agent never sees. Every step is written to the audit log.
+
+mod models;
+mod repository;
+mod sender;
+
+pub use models::{
+    ApproveMessageRequest, EmailSenderConfig, OutboxChannel, OutboxConfig, OutboxContent,
+    OutboxListQuery, OutboxMessage, OutboxStatus, RejectMessageRequest, SlackSenderConfig,
+    StageMessageRequest,
+};
+pub use repository::OutboxRepository;
+pub(crate) use sender::{address_of, format_email, sendmail};
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::{Result, bail};
+use tracing::{info, warn};
+
+use crate::audit::AuditLogger;
+use crate::ws::{WsEvent, WsHub};
+use sender::OutboxSender;
+
+/// How often stale pending messages are expired.
+const SWEEP_INTERVAL: Duration = Duration::from_secs(3600);
+
+/// Outcome of approving a message.
+pub enum ApproveOutcome {
+    /// Sent, or failed to send (see `status` and `error`).
+    Delivered(OutboxMessage),
+    /// It was reviewed or is being sent already.
+    AlreadyReviewed,
+}
+
+/// Outbox service: staging, review and delivery.
+pub struct OutboxService {
+    repo: OutboxRepository,
+    config: OutboxConfig,
+    sender: OutboxSender,
+    hub: Arc<WsHub>,
+    audit: Option<Arc<AuditLogger>>,
+}
+
+impl OutboxService {
+    pub fn new(
+        repo: OutboxRepository,
+        config: OutboxConfig,
+        hub: Arc<WsHub>,
+        audit: Option<Arc<AuditLogger>>,
+    ) -> Result<Self> {
+        let sender = OutboxSender::new(&config)?;
+        Ok(Self {
+            repo,
+            config,
+            sender,
+            hub,
+            audit,
+        })
+    }
+
+    pub fn config(&self) -> &OutboxConfig {
+        &self.config
+    }
+
+    pub fn repository(&self) -> &OutboxRepository {
+        &self.repo
+    }
+
+    /// Check a message against the channel's configuration and limits.
+    pub fn validate(&self, channel: OutboxChannel, content: &OutboxContent) -> Result<()> {
+        if content.recipients.is_empty() {
+            bail!("message has no recipients");
+        }
+        if content.recipients.len() + content.cc.len() > self.config.max_recipients {
+            bail!(
+                "at most {} recipients per message",
+                self.config.max_recipients
+            );
+        }
+        if content.body.trim().is_empty() {
+            bail!("message body is empty");
+        }
+        if content.body.len() > self.config.max_body_bytes {
+            bail!("message body exceeds {} bytes", self.config.max_body_bytes);
+        }
+        match channel {
+            OutboxChannel::Email => {
+                let email = &self.config.email;
+                if !email.enabled {
+                    bail!("email sending is not enabled");
+                }
+                if content
+                    .subject
+                    .as_deref()
+                    .is_none_or(|s| s.trim().is_empty())
+                {
+                    bail!("email needs a subject");
+                }
+                for recipient in content.recipients.iter().chain(&content.cc) {
+                    let Some(addr) = address_of(recipient) else {
+                        bail!("invalid email address '{recipient}'");
+                    };
+                    let domain = addr.rsplit_once('@').map(|(_, d)| d).unwrap_or_default();
+                    if !email.allowed_domains.is_empty()
+                        && !email
+                            .allowed_domains
+                            .iter()
+                            .any(|d| d.eq_ignore_ascii_case(domain))
+                    {
+                        bail!("recipient domain '{domain}' is not allowed");
+                    }
+                }
+            }
+            OutboxChannel::Slack => {
+                let slack = &self.config.slack;
+                if !slack.enabled {
+                    bail!("Slack sending is not enabled");
+                }
+                if content.recipients.len() != 1 || !content.cc.is_empty() {
+                    bail!("a Slack message goes to exactly one channel");
+                }
+                let channel = content.recipients[0].trim_start_matches('#');
+                if !slack.allowed_channels.is_empty()
+                    && !slack
+                        .allowed_channels
+                        .iter()
+                        .any(|c| c.trim_start_matches('#') == channel)
+                {
+                    bail!("Slack channel '{channel}' is not allowed");
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Stage a validated message and notify the user that it waits for
+    /// review.
+    pub async fn stage(
+        &self,
+        user_id: &str,
+        session_id: &str,
+        request: &StageMessageRequest,
+    ) -> Result<OutboxMessage> {
+        let message = self
+            .repo
+            .create(
+                user_id,
+                session_id,
+                request.channel,
+                &request.content,
+                request.reason.as_deref(),
+            )
+            .await?;
+        info!(
+            outbox_id = %message.id,
+            user_id = %user_id,
+            session_id = %session_id,
+            channel = %message.channel,
+            "Outbound message staged for approval"
+        );
+        self.audit("outbox_staged", user_id, &message, None).await;
+        self.hub
+            .send_to_user(
+                user_id,
+                WsEvent::Notification {
+                    level: "info".to_string(),
+                    title: "Message awaiting approval".to_string(),
+                    message: format!(
+                        "The agent wants to send {} to {}",
+                        describe(&message),
+                        message.content.recipients.join(", ")
+                    ),
+                    category: "outbox.pending".to_string(),
+                    detail: serde_json::to_value(&message).ok(),
+                },
+            )
+            .await;
+        Ok(message)
+    }
+
+    /// The message with the reviewer's edits applied, validated.
+    pub fn approved_content(
+        &self,
+        message: &OutboxMessage,
+        edits: ApproveMessageRequest,
+    ) -> Result<OutboxContent> {
+        let content = OutboxContent {
+            recipients: edits
+                .recipients
+                .unwrap_or_else(|| message.content.recipients.clone()),
+            cc: edits.cc.unwrap_or_else(|| message.content.cc.clone()),
+            subject: edits.subject.or_else(|| message.content.subject.clone()),
+            body: edits.body.unwrap_or_else(|| message.content.body.clone()),
+        };
+        self.validate(message.channel, &content)?;
+        Ok(content)
+    }
+
+    /// Approve and send a message. The caller has checked that it belongs to
+    /// `reviewer`.
+    pub async fn approve(
+        &self,
+        message: &OutboxMessage,
+        content: &OutboxContent,
+        reviewer: &str,
+    ) -> Result<ApproveOutcome> {
+        let original = (content != &message.content).then_some(&message.content);
+        if !self
+            .repo
+            .approve(&message.id, reviewer, content, original)
+            .await?
+        {
+            return Ok(ApproveOutcome::AlreadyReviewed);
+        }
+        let approved = OutboxMessage {
+            content: content.clone(),
+            status: OutboxStatus::Sending,
+            ..message.clone()
+        };
+        let note = original.map(|_| "edited by reviewer");
+        self.audit("outbox_approved", reviewer, &approved, note)
+            .await;
+
+        let outcome = self
+            .sender
+            .send(&approved)
+            .await
+            .map_err(|e| format!("{e:#}"));
+        self.repo
+            .finish_send(&message.id, outcome.as_deref().map_err(String::as_str))
+            .await?;
+        let sent = self.repo.get(&message.id).await?.unwrap_or(approved);
+        match outcome {
+            Ok(_) => {
+                info!(outbox_id = %sent.id, channel = %sent.channel, "Outbound message sent");
+                self.audit("outbox_sent", reviewer, &sent, None).await;
+            }
+            Err(error) => {
+                warn!(outbox_id = %sent.id, "Sending outbound message failed: {}", error);
+                self.audit("outbox_send_failed", reviewer, &sent, Some(&error))
+                    .await;
+                self.hub
+                    .send_to_user(
+                        &sent.user_id,
+                        WsEvent::Notification {
+                            level: "error".to_string(),
+                            title: "Message not sent".to_string(),
+                            message: format!("Sending {} failed: {}", describe(&sent), error),
+                            category: "outbox.failed".to_string(),
+                            detail: serde_json::to_value(&sent).ok(),
+                        },
+                    )
+                    .await;
+            }
+        }
+        Ok(ApproveOutcome::Delivered(sent))
+    }
+
+    /// Decline a message. Returns false if it was already reviewed.
+    pub async fn reject(
+        &self,
+        message: &OutboxMessage,
+        reviewer: &str,
+        note: Option<&str>,
+    ) -> Result<bool> {
+        let rejected = self.repo.reject(&message.id, reviewer, note).await?;
+        if rejected {
+            info!(outbox_id = %message.id, "Outbound message rejected");
+            self.audit("outbox_rejected", reviewer, message, note).await;
+        }
+        Ok(rejected)
+    }
+
+    /// Fail messages a restart interrupted, then expire unreviewed messages
+    /// hourly.
+    pub fn start_sweep_task(self: &Arc<Self>) {
+        let service = Arc::clone(self);
+        tokio::spawn(async move {
+            match service.repo.fail_interrupted().await {
+                Ok(0) => {}
+                Ok(n) => warn!(count = n, "Outbound messages interrupted by restart"),
+                Err(e) => warn!("Failing interrupted outbound messages: {:#}", e),
+            }
+            let mut interval = tokio::time::interval(SWEEP_INTERVAL);
+            interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+            loop {
+                interval.tick().await;
+                match service
+                    .repo
+                    .expire_stale(service.config.pending_ttl_days)
+                    .await
+                {
+                    Ok(0) => {}
+                    Ok(n) => info!(expired = n, "Expired outbound messages"),
+                    Err(e) => warn!("Outbox sweep failed: {:#}", e),
+                }
+            }
+        });
+    }
+
+    async fn audit(
+        &self,
+        event: &str,
+        user_id: &str,
+        message: &OutboxMessage,
+        reason: Option<&str>,
+    ) {
+        if let Some(audit) = &self.audit {
+            audit.log_outbox(event, user_id, message, reason).await;
+        }
+    }
+}
+
+/// "an email" / "a Slack message", with the subject if there is one.
+fn describe(message: &OutboxMessage) -> String {
+    match (message.channel, &message.content.subject) {
+        (OutboxChannel::Email, Some(subject)) => format!("an email \"{subject}\""),
+        (OutboxChannel::Email, None) => "an email".to_string(),
+        (OutboxChannel::Slack, _) => "a Slack message".to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::Database;
+
+    async fn service() -> OutboxService {
+        let db = Database::in_memory().await.unwrap();
+        let config = OutboxConfig {
+            enabled: true,
+            email: EmailSenderConfig {
+                enabled: true,
+                from: "agents@example.org".to_string(),
+                allowed_domains: vec!["example.com".to_string()],
+                ..Default::default()
+            },
+            slack: SlackSenderConfig {
+                enabled: true,
+                allowed_channels: vec!["#releases".to_string()],
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        OutboxService::new(
+            OutboxRepository::new(db.pool().clone()),
+            config,
+            Arc::new(WsHub::new()),
+            None,
+        )
+        .unwrap()
+    }
+
+    fn email(recipient: &str) -> OutboxContent {
+        OutboxContent {
+            recipients: vec![recipient.to_string()],
+            cc: Vec::new(),
+            subject: Some("Release".to_string()),
+            body: "v1.2 is out".to_string(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_validate_enforces_channel_rules() {
+        let service = service().await;
+        assert!(
+            service
+                .validate(OutboxChannel::Email, &email("Bob <bob@example.com>"))
+                .is_ok()
+        );
+        assert!(
+            service
+                .validate(OutboxChannel::Email, &email("eve@elsewhere.net"))
+                .is_err()
+        );
+        let mut no_subject = email("bob@example.com");
+        no_subject.subject = None;
+        assert!(service.validate(OutboxChannel::Email, &no_subject).is_err());
+
+        let slack = |channel: &str| OutboxContent {
+            recipients: vec![channel.to_string()],
+            cc: Vec::new(),
+            subject: None,
+            body: "v1.2 is out".to_string(),
+        };
+        assert!(
+            service
+                .validate(OutboxChannel::Slack, &slack("releases"))
+                .is_ok()
+        );
+        assert!(
+            service
+                .validate(OutboxChannel::Slack, &slack("#general"))
+                .is_err()
+        );
+    }
+}
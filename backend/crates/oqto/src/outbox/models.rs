@@ -0,0 +1,198 @@
+use std::path::PathBuf;
+
+use serde::{Deserialize, Serialize};
+
+/// Outbox configuration: which channels the backend may send on, and the
+/// credentials it sends with. Agents never see these.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct OutboxConfig {
+    /// Let agents stage outbound messages for approval.
+    pub enabled: bool,
+    pub email: EmailSenderConfig,
+    pub slack: SlackSenderConfig,
+    /// Most pending messages one session may stage.
+    pub max_pending_per_session: i64,
+    /// Pending messages not reviewed within this window expire.
+    pub pending_ttl_days: i64,
+    /// Longest message body accepted, in bytes.
+    pub max_body_bytes: usize,
+    /// Most recipients (to + cc) per message.
+    pub max_recipients: usize,
+}
+
+impl Default for OutboxConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            email: EmailSenderConfig::default(),
+            slack: SlackSenderConfig::default(),
+            max_pending_per_session: 20,
+            pending_ttl_days: 7,
+            max_body_bytes: 64 * 1024,
+            max_recipients: 20,
+        }
+    }
+}
+
+/// Email delivery through the host's sendmail-compatible MTA.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct EmailSenderConfig {
+    pub enabled: bool,
+    /// sendmail-compatible binary (postfix, msmtp, ...).
+    pub sendmail_path: PathBuf,
+    /// From address of every message, e.g. `"Oqto <agents@example.com>"`.
+    pub from: String,
+    /// Recipient domains allowed; empty allows any.
+    pub allowed_domains: Vec<String>,
+}
+
+impl Default for EmailSenderConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            sendmail_path: PathBuf::from("/usr/sbin/sendmail"),
+            from: String::new(),
+            allowed_domains: Vec::new(),
+        }
+    }
+}
+
+/// Slack delivery with a bot token (`chat.postMessage`).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SlackSenderConfig {
+    pub enabled: bool,
+    /// Bot token (`xoxb-...`).
+    pub bot_token: String,
+    /// Channel ids or names allowed; empty allows any the bot can post to.
+    pub allowed_channels: Vec<String>,
+    pub api_url: String,
+}
+
+impl Default for SlackSenderConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            bot_token: String::new(),
+            allowed_channels: Vec::new(),
+            api_url: "https://slack.com/api".to_string(),
+        }
+    }
+}
+
+/// Where a message goes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, sqlx::Type)]
+#[serde(rename_all = "snake_case")]
+#[sqlx(rename_all = "snake_case")]
+pub enum OutboxChannel {
+    Email,
+    Slack,
+}
+
+impl std::fmt::Display for OutboxChannel {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Email => write!(f, "email"),
+            Self::Slack => write!(f, "slack"),
+        }
+    }
+}
+
+/// Review and delivery state of an outbox message.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, sqlx::Type)]
+#[serde(rename_all = "snake_case")]
+#[sqlx(rename_all = "snake_case")]
+pub enum OutboxStatus {
+    /// Waiting for the user's review.
+    Pending,
+    /// Approved; the backend is sending it.
+    Sending,
+    Sent,
+    /// Delivery failed (`error` is set); can be approved again to retry.
+    Failed,
+    /// Declined by the user.
+    Rejected,
+    /// Not reviewed within `pending_ttl_days`.
+    Expired,
+}
+
+/// Content of a message as the agent drafted it or the reviewer sends it.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct OutboxContent {
+    /// Email addresses, or the one Slack channel.
+    pub recipients: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub cc: Vec<String>,
+    /// Email subject (unused for Slack).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub subject: Option<String>,
+    pub body: String,
+}
+
+/// A message staged by an agent.
+#[derive(Debug, Clone, Serialize)]
+pub struct OutboxMessage {
+    pub id: String,
+    pub user_id: String,
+    pub session_id: String,
+    pub channel: OutboxChannel,
+    #[serde(flatten)]
+    pub content: OutboxContent,
+    /// Why the agent wants to send it.
+    pub reason: Option<String>,
+    pub status: OutboxStatus,
+    /// The agent's draft, when the reviewer edited it.
+    pub original: Option<OutboxContent>,
+    pub reviewed_by: Option<String>,
+    pub reviewed_at: Option<String>,
+    /// Reviewer's note (e.g. why it was rejected).
+    pub review_note: Option<String>,
+    pub sent_at: Option<String>,
+    pub provider_message_id: Option<String>,
+    pub error: Option<String>,
+    pub created_at: String,
+}
+
+/// Request body for staging a message (agents, via `oqtoctl outbox`).
+#[derive(Debug, Clone, Deserialize)]
+pub struct StageMessageRequest {
+    pub channel: OutboxChannel,
+    #[serde(flatten)]
+    pub content: OutboxContent,
+    #[serde(default)]
+    pub reason: Option<String>,
+}
+
+/// Optional edits applied when approving a message.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct ApproveMessageRequest {
+    #[serde(default)]
+    pub recipients: Option<Vec<String>>,
+    #[serde(default)]
+    pub cc: Option<Vec<String>>,
+    #[serde(default)]
+    pub subject: Option<String>,
+    #[serde(default)]
+    pub body: Option<String>,
+}
+
+/// Request body for rejecting a message.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct RejectMessageRequest {
+    #[serde(default)]
+    pub note: Option<String>,
+}
+
+/// Filters for the outbox list.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct OutboxListQuery {
+    /// Defaults to pending messages.
+    #[serde(default)]
+    pub status: Option<OutboxStatus>,
+    #[serde(default)]
+    pub session_id: Option<String>,
+    #[serde(default)]
+    pub limit: Option<i64>,
+}
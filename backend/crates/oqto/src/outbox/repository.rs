@@ -0,0 +1,354 @@
+use anyhow::{Context, Result};
+use sqlx::{FromRow, QueryBuilder, Sqlite, SqlitePool};
+
+use super::{OutboxChannel, OutboxContent, OutboxListQuery, OutboxMessage, OutboxStatus};
+
+const MESSAGE_COLUMNS: &str = "id, user_id, session_id, channel, recipients, cc, subject, body, \
+     reason, status, original, reviewed_by, reviewed_at, review_note, sent_at, \
+     provider_message_id, error, created_at";
+
+/// Most messages returned by one list call.
+const MAX_LIST_LIMIT: i64 = 200;
+
+#[derive(Debug, Clone, FromRow)]
+struct MessageRow {
+    id: String,
+    user_id: String,
+    session_id: String,
+    channel: OutboxChannel,
+    recipients: String,
+    cc: String,
+    subject: Option<String>,
+    body: String,
+    reason: Option<String>,
+    status: OutboxStatus,
+    original: Option<String>,
+    reviewed_by: Option<String>,
+    reviewed_at: Option<String>,
+    review_note: Option<String>,
+    sent_at: Option<String>,
+    provider_message_id: Option<String>,
+    error: Option<String>,
+    created_at: String,
+}
+
+impl From<MessageRow> for OutboxMessage {
+    fn from(row: MessageRow) -> Self {
+        Self {
+            id: row.id,
+            user_id: row.user_id,
+            session_id: row.session_id,
+            channel: row.channel,
+            content: OutboxContent {
+                recipients: serde_json::from_str(&row.recipients).unwrap_or_default(),
+                cc: serde_json::from_str(&row.cc).unwrap_or_default(),
+                subject: row.subject,
+                body: row.body,
+            },
+            reason: row.reason,
+            status: row.status,
+            original: row
+                .original
+                .and_then(|original| serde_json::from_str(&original).ok()),
+            reviewed_by: row.reviewed_by,
+            reviewed_at: row.reviewed_at,
+            review_note: row.review_note,
+            sent_at: row.sent_at,
+            provider_message_id: row.provider_message_id,
+            error: row.error,
+            created_at: row.created_at,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct OutboxRepository {
+    pool: SqlitePool,
+}
+
+impl OutboxRepository {
+    pub fn new(pool: SqlitePool) -> Self {
+        Self { pool }
+    }
+
+    fn generate_id() -> String {
+        format!("obx_{}", nanoid::nanoid!(12))
+    }
+
+    pub async fn create(
+        &self,
+        user_id: &str,
+        session_id: &str,
+        channel: OutboxChannel,
+        content: &OutboxContent,
+        reason: Option<&str>,
+    ) -> Result<OutboxMessage> {
+        let id = Self::generate_id();
+        sqlx::query(
+            r#"INSERT INTO outbox_messages
+                   (id, user_id, session_id, channel, recipients, cc, subject, body, reason)
+               VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?)"#,
+        )
+        .bind(&id)
+        .bind(user_id)
+        .bind(session_id)
+        .bind(channel)
+        .bind(serde_json::to_string(&content.recipients)?)
+        .bind(serde_json::to_string(&content.cc)?)
+        .bind(&content.subject)
+        .bind(&content.body)
+        .bind(reason)
+        .execute(&self.pool)
+        .await
+        .context("insert outbox message")?;
+
+        self.get(&id)
+            .await?
+            .ok_or_else(|| anyhow::anyhow!("Outbox message not found after creation"))
+    }
+
+    pub async fn get(&self, id: &str) -> Result<Option<OutboxMessage>> {
+        let row = sqlx::query_as::<_, MessageRow>(&format!(
+            "SELECT {MESSAGE_COLUMNS} FROM outbox_messages WHERE id = ?"
+        ))
+        .bind(id)
+        .fetch_optional(&self.pool)
+        .await
+        .context("get outbox message")?;
+        Ok(row.map(Into::into))
+    }
+
+    /// A user's messages, newest first.
+    pub async fn list_for_user(
+        &self,
+        user_id: &str,
+        query: &OutboxListQuery,
+    ) -> Result<Vec<OutboxMessage>> {
+        let mut qb = QueryBuilder::<Sqlite>::new(format!(
+            "SELECT {MESSAGE_COLUMNS} FROM outbox_messages WHERE user_id = "
+        ));
+        qb.push_bind(user_id.to_string());
+        qb.push(" AND status = ")
+            .push_bind(query.status.unwrap_or(OutboxStatus::Pending));
+        if let Some(session_id) = &query.session_id {
+            qb.push(" AND session_id = ").push_bind(session_id.clone());
+        }
+        qb.push(" ORDER BY created_at DESC, id DESC LIMIT ")
+            .push_bind(query.limit.unwrap_or(50).clamp(1, MAX_LIST_LIMIT));
+
+        let rows = qb
+            .build_query_as::<MessageRow>()
+            .fetch_all(&self.pool)
+            .await
+            .context("list outbox messages")?;
+        Ok(rows.into_iter().map(Into::into).collect())
+    }
+
+    pub async fn count_pending(&self, session_id: &str) -> Result<i64> {
+        let (count,) = sqlx::query_as::<_, (i64,)>(
+            "SELECT COUNT(*) FROM outbox_messages WHERE session_id = ? AND status = 'pending'",
+        )
+        .bind(session_id)
+        .fetch_one(&self.pool)
+        .await
+        .context("count pending outbox messages")?;
+        Ok(count)
+    }
+
+    /// Approve a pending (or failed) message for sending with `content`.
+    /// `original` is the agent's draft if the reviewer changed it. Returns
+    /// false if the message was reviewed or is being sent concurrently.
+    pub async fn approve(
+        &self,
+        id: &str,
+        reviewer: &str,
+        content: &OutboxContent,
+        original: Option<&OutboxContent>,
+    ) -> Result<bool> {
+        let original = original.map(serde_json::to_string).transpose()?;
+        let result = sqlx::query(
+            r#"UPDATE outbox_messages
+               SET status = 'sending', recipients = ?, cc = ?, subject = ?, body = ?,
+                   original = COALESCE(original, ?), reviewed_by = ?,
+                   reviewed_at = datetime('now'), error = NULL
+               WHERE id = ? AND status IN ('pending', 'failed')"#,
+        )
+        .bind(serde_json::to_string(&content.recipients)?)
+        .bind(serde_json::to_string(&content.cc)?)
+        .bind(&content.subject)
+        .bind(&content.body)
+        .bind(original)
+        .bind(reviewer)
+        .bind(id)
+        .execute(&self.pool)
+        .await
+        .context("approve outbox message")?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Record the outcome of sending an approved message.
+    pub async fn finish_send(&self, id: &str, outcome: Result<&str, &str>) -> Result<()> {
+        let (status, provider_message_id, error) = match outcome {
+            Ok(provider_id) => (OutboxStatus::Sent, Some(provider_id), None),
+            Err(error) => (OutboxStatus::Failed, None, Some(error)),
+        };
+        sqlx::query(
+            r#"UPDATE outbox_messages
+               SET status = ?, provider_message_id = ?, error = ?,
+                   sent_at = CASE WHEN ? = 'sent' THEN datetime('now') ELSE sent_at END
+               WHERE id = ? AND status = 'sending'"#,
+        )
+        .bind(status)
+        .bind(provider_message_id)
+        .bind(error)
+        .bind(status)
+        .bind(id)
+        .execute(&self.pool)
+        .await
+        .context("record outbox send result")?;
+        Ok(())
+    }
+
+    /// Decline a pending or failed message. Returns false if it was already
+    /// reviewed.
+    pub async fn reject(&self, id: &str, reviewer: &str, note: Option<&str>) -> Result<bool> {
+        let result = sqlx::query(
+            r#"UPDATE outbox_messages
+               SET status = 'rejected', reviewed_by = ?, reviewed_at = datetime('now'),
+                   review_note = ?
+               WHERE id = ? AND status IN ('pending', 'failed')"#,
+        )
+        .bind(reviewer)
+        .bind(note)
+        .bind(id)
+        .execute(&self.pool)
+        .await
+        .context("reject outbox message")?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Expire pending messages older than `ttl_days`.
+    pub async fn expire_stale(&self, ttl_days: i64) -> Result<u64> {
+        let result = sqlx::query(
+            r#"UPDATE outbox_messages
+               SET status = 'expired', reviewed_at = datetime('now')
+               WHERE status = 'pending' AND created_at <= datetime('now', ?)"#,
+        )
+        .bind(format!("-{ttl_days} days"))
+        .execute(&self.pool)
+        .await
+        .context("expire outbox messages")?;
+        Ok(result.rows_affected())
+    }
+
+    /// Mark messages left in `sending` by a restart as failed. Whether they
+    /// went out is unknown, so they are not retried automatically.
+    pub async fn fail_interrupted(&self) -> Result<u64> {
+        let result = sqlx::query(
+            r#"UPDATE outbox_messages
+               SET status = 'failed', error = 'interrupted by a server restart; delivery unknown'
+               WHERE status = 'sending'"#,
+        )
+        .execute(&self.pool)
+        .await
+        .context("fail interrupted outbox messages")?;
+        Ok(result.rows_affected())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::Database;
+
+    async fn repo() -> OutboxRepository {
+        let db = Database::in_memory().await.unwrap();
+        sqlx::query("INSERT INTO users (id, username, email, display_name) VALUES (?, ?, ?, ?)")
+            .bind("alice")
+            .bind("alice")
+            .bind("alice@example.com")
+            .bind("Alice")
+            .execute(db.pool())
+            .await
+            .unwrap();
+        OutboxRepository::new(db.pool().clone())
+    }
+
+    fn draft(body: &str) -> OutboxContent {
+        OutboxContent {
+            recipients: vec!["bob@example.com".to_string()],
+            cc: Vec::new(),
+            subject: Some("Release notes".to_string()),
+            body: body.to_string(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_outbox_review_lifecycle() {
+        let repo = repo().await;
+        let message = repo
+            .create(
+                "alice",
+                "ses_1",
+                OutboxChannel::Email,
+                &draft("v1.2 is out"),
+                Some("user asked to notify Bob"),
+            )
+            .await
+            .unwrap();
+        assert_eq!(message.status, OutboxStatus::Pending);
+        assert_eq!(message.content, draft("v1.2 is out"));
+        assert_eq!(repo.count_pending("ses_1").await.unwrap(), 1);
+
+        let edited = draft("v1.2 is out, see the changelog");
+        assert!(
+            repo.approve(&message.id, "alice", &edited, Some(&message.content))
+                .await
+                .unwrap()
+        );
+        // Approving twice is refused while the first send is in flight.
+        assert!(
+            !repo
+                .approve(&message.id, "alice", &edited, None)
+                .await
+                .unwrap()
+        );
+
+        repo.finish_send(&message.id, Err("sendmail exited with 75"))
+            .await
+            .unwrap();
+        let failed = repo.get(&message.id).await.unwrap().unwrap();
+        assert_eq!(failed.status, OutboxStatus::Failed);
+        assert_eq!(failed.content, edited);
+        assert_eq!(failed.original, Some(draft("v1.2 is out")));
+
+        // A failed message can be approved again; the agent's draft stays.
+        assert!(
+            repo.approve(&message.id, "alice", &edited, None)
+                .await
+                .unwrap()
+        );
+        repo.finish_send(&message.id, Ok("<id@example.com>"))
+            .await
+            .unwrap();
+        let sent = repo.get(&message.id).await.unwrap().unwrap();
+        assert_eq!(sent.status, OutboxStatus::Sent);
+        assert!(sent.sent_at.is_some());
+        assert!(sent.error.is_none());
+        assert_eq!(sent.original, Some(draft("v1.2 is out")));
+        assert!(!repo.reject(&message.id, "alice", None).await.unwrap());
+
+        let other = repo
+            .create("alice", "ses_1", OutboxChannel::Email, &draft("x"), None)
+            .await
+            .unwrap();
+        let pending = repo
+            .list_for_user("alice", &OutboxListQuery::default())
+            .await
+            .unwrap();
+        assert_eq!(pending.len(), 1);
+        assert_eq!(pending[0].id, other.id);
+        assert_eq!(repo.expire_stale(0).await.unwrap(), 1);
+        assert_eq!(repo.count_pending("ses_1").await.unwrap(), 0);
+    }
+}
@@ -0,0 +1,264 @@
+//! Delivery of approved messages. Only the backend holds the credentials
+//! used here.
+
+use std::process::Stdio;
+use std::time::Duration;
+
+use anyhow::{Context, Result, bail};
+use base64::Engine as _;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use tokio::io::AsyncWriteExt;
+use tokio::process::Command;
+
+use super::{
+    EmailSenderConfig, OutboxChannel, OutboxConfig, OutboxContent, OutboxMessage, SlackSenderConfig,
+};
+
+const SEND_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Sends approved messages on their channel.
+pub struct OutboxSender {
+    email: EmailSenderConfig,
+    slack: SlackSenderConfig,
+    client: reqwest::Client,
+}
+
+impl OutboxSender {
+    pub fn new(config: &OutboxConfig) -> Result<Self> {
+        let client = reqwest::Client::builder()
+            .timeout(SEND_TIMEOUT)
+            .build()
+            .context("building outbox HTTP client")?;
+        Ok(Self {
+            email: config.email.clone(),
+            slack: config.slack.clone(),
+            client,
+        })
+    }
+
+    /// Deliver a message. Returns the provider's message id.
+    pub async fn send(&self, message: &OutboxMessage) -> Result<String> {
+        match message.channel {
+            OutboxChannel::Email => self.send_email(message).await,
+            OutboxChannel::Slack => self.send_slack(message).await,
+        }
+    }
+
+    async fn send_email(&self, message: &OutboxMessage) -> Result<String> {
+        let (message_id, rfc822) = format_email(
+            &self.email.from,
+            &message.content,
+            &[("X-Oqto-Outbox-Id", message.id.clone())],
+        )?;
+        let recipients: Vec<_> = message
+            .content
+            .recipients
+            .iter()
+            .chain(&message.content.cc)
+            .cloned()
+            .collect();
+        sendmail(&self.email, &recipients, &rfc822).await?;
+        Ok(message_id)
+    }
+
+    async fn send_slack(&self, message: &OutboxMessage) -> Result<String> {
+        let channel = message
+            .content
+            .recipients
+            .first()
+            .context("slack message has no channel")?;
+        let response: serde_json::Value = self
+            .client
+            .post(format!(
+                "{}/chat.postMessage",
+                self.slack.api_url.trim_end_matches('/')
+            ))
+            .bearer_auth(&self.slack.bot_token)
+            .json(&serde_json::json!({
+                "channel": channel,
+                "text": message.content.body,
+            }))
+            .send()
+            .await
+            .context("posting to Slack")?
+            .error_for_status()
+            .context("posting to Slack")?
+            .json()
+            .await
+            .context("reading Slack response")?;
+        if response["ok"].as_bool() != Some(true) {
+            bail!(
+                "Slack refused the message: {}",
+                response["error"].as_str().unwrap_or("unknown error")
+            );
+        }
+        Ok(response["ts"].as_str().unwrap_or_default().to_string())
+    }
+}
+
+/// Pipe an RFC 5322 message to the configured sendmail.
+pub(crate) async fn sendmail(
+    config: &EmailSenderConfig,
+    recipients: &[String],
+    rfc822: &str,
+) -> Result<()> {
+    let mut child = Command::new(&config.sendmail_path)
+        .arg("-i")
+        .arg("--")
+        .args(recipients)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true)
+        .spawn()
+        .with_context(|| format!("starting {}", config.sendmail_path.display()))?;
+    let mut stdin = child.stdin.take().context("sendmail stdin")?;
+    let output = tokio::time::timeout(SEND_TIMEOUT, async {
+        stdin.write_all(rfc822.as_bytes()).await?;
+        drop(stdin);
+        child.wait_with_output().await
+    })
+    .await
+    .context("sendmail timed out")?
+    .context("running sendmail")?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        bail!(
+            "sendmail exited with {}: {}",
+            output.status,
+            stderr.trim().chars().take(200).collect::<String>()
+        );
+    }
+    Ok(())
+}
+
+/// Build the RFC 5322 message with `extra_headers` appended. Returns its
+/// Message-ID and the text.
+pub(crate) fn format_email(
+    from: &str,
+    content: &OutboxContent,
+    extra_headers: &[(&str, String)],
+) -> Result<(String, String)> {
+    let domain = address_of(from)
+        .and_then(|addr| addr.rsplit_once('@'))
+        .map(|(_, domain)| domain)
+        .context("email.from has no domain")?;
+    let message_id = format!("<{}@{}>", nanoid::nanoid!(16), domain);
+
+    let mut headers = vec![
+        ("From", from.to_string()),
+        ("To", content.recipients.join(", ")),
+    ];
+    if !content.cc.is_empty() {
+        headers.push(("Cc", content.cc.join(", ")));
+    }
+    headers.extend([
+        (
+            "Subject",
+            encode_header(content.subject.as_deref().unwrap_or_default()),
+        ),
+        ("Date", chrono::Utc::now().to_rfc2822()),
+        ("Message-ID", message_id.clone()),
+        ("MIME-Version", "1.0".to_string()),
+        ("Content-Type", "text/plain; charset=utf-8".to_string()),
+        ("Content-Transfer-Encoding", "base64".to_string()),
+    ]);
+    headers.extend(extra_headers.iter().cloned());
+
+    let mut out = String::new();
+    for (name, value) in headers {
+        if value.contains(['\r', '\n']) {
+            bail!("{name} header contains a line break");
+        }
+        out.push_str(&format!("{name}: {value}\r\n"));
+    }
+    out.push_str("\r\n");
+    let body = BASE64.encode(content.body.as_bytes());
+    for line in body.as_bytes().chunks(76) {
+        out.push_str(std::str::from_utf8(line).unwrap_or_default());
+        out.push_str("\r\n");
+    }
+    Ok((message_id, out))
+}
+
+/// RFC 2047 encoded-word for non-ASCII header text.
+fn encode_header(value: &str) -> String {
+    if value.is_ascii() {
+        value.to_string()
+    } else {
+        format!("=?utf-8?B?{}?=", BASE64.encode(value.as_bytes()))
+    }
+}
+
+/// The bare address of `Name <addr>` or `addr`.
+pub(crate) fn address_of(mailbox: &str) -> Option<&str> {
+    let addr = match mailbox.rsplit_once('<') {
+        Some((_, rest)) => rest.strip_suffix('>')?,
+        None => mailbox,
+    }
+    .trim();
+    let (local, domain) = addr.split_once('@')?;
+    let valid = !local.is_empty()
+        && domain.contains('.')
+        && !addr.contains(|c: char| c.is_whitespace() || c.is_control() || "<>,;\"".contains(c));
+    valid.then_some(addr)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::outbox::OutboxStatus;
+
+    #[test]
+    fn test_format_email_encodes_subject_and_body() {
+        let message = OutboxMessage {
+            id: "obx_1".to_string(),
+            user_id: "alice".to_string(),
+            session_id: "ses_1".to_string(),
+            channel: OutboxChannel::Email,
+            content: OutboxContent {
+                recipients: vec!["bob@example.com".to_string()],
+                cc: vec!["carol@example.com".to_string()],
+                subject: Some("Grüße".to_string()),
+                body: "Hallo Bob".to_string(),
+            },
+            reason: None,
+            status: OutboxStatus::Sending,
+            original: None,
+            reviewed_by: None,
+            reviewed_at: None,
+            review_note: None,
+            sent_at: None,
+            provider_message_id: None,
+            error: None,
+            created_at: String::new(),
+        };
+        let (message_id, text) = format_email(
+            "Oqto <agents@example.org>",
+            &message.content,
+            &[("X-Oqto-Outbox-Id", message.id.clone())],
+        )
+        .unwrap();
+        assert!(message_id.ends_with("@example.org>"));
+        assert!(text.contains("To: bob@example.com\r\nCc: carol@example.com\r\n"));
+        assert!(text.contains("Subject: =?utf-8?B?R3LDvMOfZQ==?=\r\n"));
+        assert!(text.contains("X-Oqto-Outbox-Id: obx_1\r\n"));
+        assert!(text.ends_with("\r\n\r\nSGFsbG8gQm9i\r\n"));
+
+        let mut injected = message.clone();
+        injected.content.subject = Some("hi\r\nBcc: eve@example.com".to_string());
+        assert!(format_email("agents@example.org", &injected.content, &[]).is_err());
+    }
+
+    #[test]
+    fn test_address_of() {
+        assert_eq!(
+            address_of("Oqto <agents@example.org>"),
+            Some("agents@example.org")
+        );
+        assert_eq!(address_of("bob@example.com"), Some("bob@example.com"));
+        assert_eq!(address_of("bob@localhost"), None);
+        assert_eq!(address_of("bob smith@example.com"), None);
+        assert_eq!(address_of("a@example.com, b@example.com"), None);
+    }
+}
@@ -0,0 +1,292 @@
+//! Priority lanes for LLM traffic sharing one EAVS deployment.
+//!
+//! Traffic is either interactive (a user waits for the reply) or background
+//! (scheduled jobs, catch-up runs, classification calls). The lane reaches
+//! EAVS in two ways: session keys carry `lane = "interactive"` in their
+//! metadata, and completions the backend sends itself carry the
+//! `X-Oqto-Lane` header.
+//!
+//! Locally, background jobs share a concurrency limit that drops to
+//! `busy_background_concurrency` while a user prompted a session within the
+//! last `interactive_window_secs`. Scheduled runs take a slot when they
+//! report `running` and give it back with their result; a run that finds
+//! the lane full is told to retry later. Catch-up runs wait for a slot, but
+//! never longer than `max_background_wait_secs`.
+
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+
+use dashmap::DashMap;
+use serde::{Deserialize, Serialize};
+use tokio::sync::Notify;
+use tracing::warn;
+
+/// `[priority_lanes]` config section.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PriorityLanesConfig {
+    pub enabled: bool,
+    /// Background jobs allowed at once while no one is chatting (0 = no
+    /// limit).
+    pub background_concurrency: usize,
+    /// Background jobs allowed at once while interactive sessions are
+    /// active (0 = no limit).
+    pub busy_background_concurrency: usize,
+    /// How long after the last prompt a user still counts as active.
+    pub interactive_window_secs: u64,
+    /// Longest a catch-up run waits for a slot before running anyway.
+    pub max_background_wait_secs: u64,
+    /// Slots of scheduled runs that never reported a result are freed after
+    /// this long.
+    pub background_run_timeout_secs: u64,
+}
+
+impl Default for PriorityLanesConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            background_concurrency: 4,
+            busy_background_concurrency: 1,
+            interactive_window_secs: 120,
+            max_background_wait_secs: 300,
+            background_run_timeout_secs: 3600,
+        }
+    }
+}
+
+/// Current lane usage.
+#[derive(Debug, Clone, Serialize)]
+pub struct LaneStats {
+    pub interactive_active: bool,
+    pub background_running: usize,
+    /// Current background limit (None = no limit).
+    pub background_limit: Option<usize>,
+}
+
+/// Tracks interactive activity and hands out background slots.
+pub struct PriorityLanes {
+    config: PriorityLanesConfig,
+    started: Instant,
+    /// Milliseconds since `started` of the last interactive command, plus
+    /// one (0 = none yet).
+    last_interactive_ms: AtomicU64,
+    running: AtomicUsize,
+    released: Notify,
+    /// Slots held by scheduled runs, by run id.
+    held: DashMap<String, (Instant, BackgroundPermit)>,
+}
+
+impl PriorityLanes {
+    pub fn new(config: PriorityLanesConfig) -> Self {
+        Self {
+            config,
+            started: Instant::now(),
+            last_interactive_ms: AtomicU64::new(0),
+            running: AtomicUsize::new(0),
+            released: Notify::new(),
+            held: DashMap::new(),
+        }
+    }
+
+    pub fn config(&self) -> &PriorityLanesConfig {
+        &self.config
+    }
+
+    /// Record a user-driven command (prompt, steer, follow-up).
+    pub fn mark_interactive(&self) {
+        let now = self.started.elapsed().as_millis() as u64 + 1;
+        self.last_interactive_ms.store(now, Ordering::Relaxed);
+    }
+
+    /// Whether a user prompted a session within the interactive window.
+    pub fn interactive_active(&self) -> bool {
+        let last = self.last_interactive_ms.load(Ordering::Relaxed);
+        if last == 0 {
+            return false;
+        }
+        let now = self.started.elapsed().as_millis() as u64 + 1;
+        now.saturating_sub(last) < self.config.interactive_window_secs * 1000
+    }
+
+    /// Background jobs allowed at once right now (None = no limit).
+    pub fn background_limit(&self) -> Option<usize> {
+        let limit = if self.interactive_active() {
+            self.config.busy_background_concurrency
+        } else {
+            self.config.background_concurrency
+        };
+        (limit > 0).then_some(limit)
+    }
+
+    /// Take a background slot if one is free.
+    pub fn try_acquire_background(self: &Arc<Self>) -> Option<BackgroundPermit> {
+        self.expire_held();
+        let limit = self.background_limit();
+        let mut current = self.running.load(Ordering::Acquire);
+        loop {
+            if limit.is_some_and(|limit| current >= limit) {
+                return None;
+            }
+            match self.running.compare_exchange_weak(
+                current,
+                current + 1,
+                Ordering::AcqRel,
+                Ordering::Acquire,
+            ) {
+                Ok(_) => {
+                    return Some(BackgroundPermit {
+                        lanes: Arc::clone(self),
+                    });
+                }
+                Err(actual) => current = actual,
+            }
+        }
+    }
+
+    /// Wait for a background slot. After `max_background_wait_secs` the job
+    /// runs anyway, without a slot, so background work is delayed but never
+    /// starved.
+    pub async fn acquire_background(self: &Arc<Self>, job: &str) -> Option<BackgroundPermit> {
+        let deadline = Instant::now() + Duration::from_secs(self.config.max_background_wait_secs);
+        loop {
+            if let Some(permit) = self.try_acquire_background() {
+                return Some(permit);
+            }
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            if remaining.is_zero() {
+                warn!(job, "Background lane still busy, running without a slot");
+                return None;
+            }
+            // The limit also rises when the interactive window ends, which
+            // notifies no one; poll as well.
+            let poll = remaining.min(Duration::from_secs(1));
+            let _ = tokio::time::timeout(poll, self.released.notified()).await;
+        }
+    }
+
+    /// Keep a slot until the scheduled run reports its result.
+    pub fn hold_run(&self, run_id: &str, permit: BackgroundPermit) {
+        self.held
+            .insert(run_id.to_string(), (Instant::now(), permit));
+    }
+
+    /// Free the slot of a finished scheduled run.
+    pub fn release_run(&self, run_id: &str) {
+        self.held.remove(run_id);
+    }
+
+    /// Whether the scheduled run already holds a slot.
+    pub fn holds_run(&self, run_id: &str) -> bool {
+        self.held.contains_key(run_id)
+    }
+
+    pub fn snapshot(&self) -> LaneStats {
+        LaneStats {
+            interactive_active: self.interactive_active(),
+            background_running: self.running.load(Ordering::Acquire),
+            background_limit: self.background_limit(),
+        }
+    }
+
+    fn expire_held(&self) {
+        let timeout = Duration::from_secs(self.config.background_run_timeout_secs);
+        self.held.retain(|run_id, (since, _)| {
+            let keep = since.elapsed() < timeout;
+            if !keep {
+                warn!(run_id = %run_id, "Scheduled run never reported a result, freeing its slot");
+            }
+            keep
+        });
+    }
+}
+
+/// A background slot, freed on drop.
+pub struct BackgroundPermit {
+    lanes: Arc<PriorityLanes>,
+}
+
+impl Drop for BackgroundPermit {
+    fn drop(&mut self) {
+        self.lanes.running.fetch_sub(1, Ordering::AcqRel);
+        self.lanes.released.notify_one();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lanes(configure: impl FnOnce(&mut PriorityLanesConfig)) -> Arc<PriorityLanes> {
+        let mut config = PriorityLanesConfig {
+            background_concurrency: 2,
+            busy_background_concurrency: 1,
+            max_background_wait_secs: 0,
+            ..Default::default()
+        };
+        configure(&mut config);
+        Arc::new(PriorityLanes::new(config))
+    }
+
+    #[test]
+    fn test_interactive_activity_lowers_limit() {
+        let lanes = lanes(|_| {});
+        assert!(!lanes.interactive_active());
+        assert_eq!(lanes.background_limit(), Some(2));
+
+        lanes.mark_interactive();
+        assert!(lanes.interactive_active());
+        assert_eq!(lanes.background_limit(), Some(1));
+
+        let idle_window = self::lanes(|c| c.interactive_window_secs = 0);
+        idle_window.mark_interactive();
+        assert!(!idle_window.interactive_active());
+    }
+
+    #[test]
+    fn test_permits_respect_limit() {
+        let lanes = lanes(|_| {});
+        let first = lanes.try_acquire_background().unwrap();
+        let _second = lanes.try_acquire_background().unwrap();
+        assert!(lanes.try_acquire_background().is_none());
+
+        drop(first);
+        assert_eq!(lanes.snapshot().background_running, 1);
+        lanes.mark_interactive();
+        assert!(lanes.try_acquire_background().is_none());
+
+        let unlimited = self::lanes(|c| c.background_concurrency = 0);
+        let permits: Vec<_> = (0..10)
+            .filter_map(|_| unlimited.try_acquire_background())
+            .collect();
+        assert_eq!(permits.len(), 10);
+    }
+
+    #[test]
+    fn test_held_runs() {
+        let lanes = lanes(|c| c.background_concurrency = 1);
+        let permit = lanes.try_acquire_background().unwrap();
+        lanes.hold_run("run_1", permit);
+        assert!(lanes.holds_run("run_1"));
+        assert!(lanes.try_acquire_background().is_none());
+
+        lanes.release_run("run_1");
+        assert!(lanes.try_acquire_background().is_some());
+
+        let expiring = self::lanes(|c| {
+            c.background_concurrency = 1;
+            c.background_run_timeout_secs = 0;
+        });
+        let permit = expiring.try_acquire_background().unwrap();
+        expiring.hold_run("run_1", permit);
+        assert!(expiring.try_acquire_background().is_some());
+        assert!(!expiring.holds_run("run_1"));
+    }
+
+    #[tokio::test]
+    async fn test_acquire_background_gives_up_after_max_wait() {
+        let lanes = lanes(|c| c.background_concurrency = 1);
+        let _held = lanes.try_acquire_background().unwrap();
+        assert!(lanes.acquire_background("test").await.is_none());
+    }
+}
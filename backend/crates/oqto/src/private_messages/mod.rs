@@ -0,0 +1,286 @@
+//! Messages kept private to the session owner.
+//!
+//! In a session other people can read, either through a shared workspace or
+//! a session share, the owner can mark a message, or one part of it such as
+//! a tool result holding a pasted credential, as private. Every read path
+//! that serves the session to someone other than the owner (message lists
+//! over REST and WebSocket, exports, fork points and history search) then
+//! replaces the content with an [`ELIDED_PART_TYPE`] placeholder, or drops
+//! the search hit, so viewers can tell that something was left out. Files
+//! produced by hidden tool calls are left out of artifact lists and
+//! exports and are not served.
+
+mod models;
+mod repository;
+
+pub use models::{MarkPrivateRequest, PrivateMessage};
+pub use repository::{NewPrivateMessage, PrivateMessageRepository};
+
+use oqto_protocol::events::{Event, EventPayload};
+use oqto_protocol::projection::{ProjectedChatMessage, ProjectedChatMessagePart};
+
+/// Part type of the placeholders that replace private content.
+pub const ELIDED_PART_TYPE: &str = "elided";
+
+/// Text of the placeholders, for clients that render unknown parts as text.
+pub const ELIDED_TEXT: &str = "[private: hidden by the session owner]";
+
+fn placeholder(id: String) -> ProjectedChatMessagePart {
+    ProjectedChatMessagePart {
+        id,
+        part_type: ELIDED_PART_TYPE.to_string(),
+        text: Some(ELIDED_TEXT.to_string()),
+        text_html: None,
+        tool_name: None,
+        tool_call_id: None,
+        tool_input: None,
+        tool_output: None,
+        tool_status: None,
+        tool_title: None,
+    }
+}
+
+/// Whether any of a message is hidden.
+pub fn is_hidden(hidden: &[PrivateMessage], message_id: &str) -> bool {
+    hidden.iter().any(|mark| mark.message_id == message_id)
+}
+
+/// Tool calls in the `hidden` messages and parts, whose attachments are
+/// hidden with them.
+pub fn hidden_tool_calls(
+    messages: &[ProjectedChatMessage],
+    hidden: &[PrivateMessage],
+) -> Vec<String> {
+    messages
+        .iter()
+        .flat_map(|message| {
+            let marks: Vec<&PrivateMessage> = hidden
+                .iter()
+                .filter(|mark| mark.message_id == message.id)
+                .collect();
+            message.parts.iter().filter(move |part| {
+                marks
+                    .iter()
+                    .any(|mark| mark.part_id.as_deref().is_none_or(|id| id == part.id))
+            })
+        })
+        .filter_map(|part| part.tool_call_id.clone())
+        .collect()
+}
+
+/// Whether an attachment produced by `tool_call_id` is hidden with its tool
+/// call.
+pub fn is_attachment_hidden(tool_call_id: Option<&str>, hidden_tool_calls: &[String]) -> bool {
+    tool_call_id.is_some_and(|id| hidden_tool_calls.iter().any(|hidden| hidden == id))
+}
+
+/// Replace the `hidden` messages and parts with placeholders. Returns the
+/// number of messages that lost content.
+pub fn elide(messages: &mut [ProjectedChatMessage], hidden: &[PrivateMessage]) -> usize {
+    let mut elided = 0;
+    for message in messages.iter_mut() {
+        let marks: Vec<&PrivateMessage> = hidden
+            .iter()
+            .filter(|mark| mark.message_id == message.id)
+            .collect();
+        if marks.is_empty() {
+            continue;
+        }
+        if marks.iter().any(|mark| mark.part_id.is_none()) {
+            message.parts = vec![placeholder(format!("{}-elided", message.id))];
+            message.summary_title = None;
+        } else {
+            for part in message.parts.iter_mut() {
+                if marks
+                    .iter()
+                    .any(|mark| mark.part_id.as_deref() == Some(part.id.as_str()))
+                {
+                    *part = placeholder(std::mem::take(&mut part.id));
+                }
+            }
+        }
+        elided += 1;
+    }
+    elided
+}
+
+/// Drop the buffered events that carry content of the `hidden` messages:
+/// their stream, the tool calls in them (`hidden_tool_calls`) and those
+/// calls' artifacts. Events cannot be elided part by part, so a message with
+/// any hidden part loses all of its events. Returns the number dropped.
+pub fn drop_hidden_events(
+    events: &mut Vec<Event>,
+    hidden: &[PrivateMessage],
+    hidden_tool_calls: &[String],
+) -> usize {
+    let before = events.len();
+    events.retain_mut(|event| {
+        if let EventPayload::Messages { messages } = &mut event.payload {
+            messages.retain(|message| !is_hidden(hidden, &message.id));
+            return true;
+        }
+        let message_id = match &event.payload {
+            EventPayload::StreamMessageStart { message_id, .. }
+            | EventPayload::StreamTextDelta { message_id, .. }
+            | EventPayload::StreamThinkingDelta { message_id, .. }
+            | EventPayload::StreamToolCallStart { message_id, .. }
+            | EventPayload::StreamToolCallDelta { message_id, .. }
+            | EventPayload::StreamToolCallEnd { message_id, .. }
+            | EventPayload::StreamMessageLint { message_id, .. } => Some(message_id.as_str()),
+            EventPayload::StreamMessageEnd { message } => Some(message.id.as_str()),
+            _ => None,
+        };
+        if message_id.is_some_and(|id| is_hidden(hidden, id)) {
+            return false;
+        }
+        let tool_call_id = match &event.payload {
+            EventPayload::ToolStart { tool_call_id, .. }
+            | EventPayload::ToolProgress { tool_call_id, .. }
+            | EventPayload::ToolOutputDelta { tool_call_id, .. }
+            | EventPayload::ToolEnd { tool_call_id, .. }
+            | EventPayload::ToolRateLimited { tool_call_id, .. }
+            | EventPayload::ToolApprovalRequired { tool_call_id, .. }
+            | EventPayload::ToolApprovalResolved { tool_call_id, .. } => {
+                Some(tool_call_id.as_str())
+            }
+            EventPayload::ArtifactCreated { artifact } => artifact.tool_call_id.as_deref(),
+            _ => None,
+        };
+        !is_attachment_hidden(tool_call_id, hidden_tool_calls)
+    });
+    before - events.len()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn part(id: &str, text: &str) -> ProjectedChatMessagePart {
+        ProjectedChatMessagePart {
+            text: Some(text.to_string()),
+            part_type: "text".to_string(),
+            ..placeholder(id.to_string())
+        }
+    }
+
+    fn message(id: &str, parts: Vec<ProjectedChatMessagePart>) -> ProjectedChatMessage {
+        ProjectedChatMessage {
+            id: id.to_string(),
+            session_id: "ses_1".to_string(),
+            role: "user".to_string(),
+            created_at: 0,
+            completed_at: None,
+            parent_id: None,
+            model_id: None,
+            provider_id: None,
+            agent: None,
+            summary_title: Some("deploy with the key".to_string()),
+            tokens_input: None,
+            tokens_output: None,
+            tokens_reasoning: None,
+            cost: None,
+            client_id: None,
+            parts,
+        }
+    }
+
+    fn mark(message_id: &str, part_id: Option<&str>) -> PrivateMessage {
+        PrivateMessage {
+            id: format!("pm_{message_id}"),
+            session_id: "ses_1".to_string(),
+            message_id: message_id.to_string(),
+            part_id: part_id.map(str::to_string),
+            owner_user_id: "alice".to_string(),
+            workspace_id: None,
+            created_at: String::new(),
+        }
+    }
+
+    #[test]
+    fn hidden_messages_and_parts_become_placeholders() {
+        let mut messages = vec![
+            message(
+                "m1",
+                vec![part("p1", "AWS_SECRET=abc"), part("p2", "deploy")],
+            ),
+            message("m2", vec![part("p3", "ok"), part("p4", "token: xyz")]),
+            message("m3", vec![part("p5", "done")]),
+        ];
+        messages[1].parts[1].tool_call_id = Some("call_1".to_string());
+        let hidden = vec![mark("m1", None), mark("m2", Some("p4"))];
+        assert!(is_hidden(&hidden, "m2"));
+        assert!(!is_hidden(&hidden, "m3"));
+        let hidden_calls = hidden_tool_calls(&messages, &hidden);
+        assert_eq!(hidden_calls, vec!["call_1"]);
+        assert!(is_attachment_hidden(Some("call_1"), &hidden_calls));
+        assert!(!is_attachment_hidden(Some("call_2"), &hidden_calls));
+        assert!(!is_attachment_hidden(None, &hidden_calls));
+
+        assert_eq!(elide(&mut messages, &hidden), 2);
+
+        assert_eq!(messages[0].parts.len(), 1);
+        assert_eq!(messages[0].parts[0].part_type, ELIDED_PART_TYPE);
+        assert_eq!(messages[0].parts[0].text.as_deref(), Some(ELIDED_TEXT));
+        assert_eq!(messages[0].summary_title, None);
+
+        assert_eq!(messages[1].parts[0].text.as_deref(), Some("ok"));
+        assert_eq!(messages[1].parts[1].id, "p4");
+        assert_eq!(messages[1].parts[1].part_type, ELIDED_PART_TYPE);
+
+        assert_eq!(messages[2].parts[0].text.as_deref(), Some("done"));
+    }
+
+    fn event(payload: EventPayload) -> Event {
+        Event {
+            session_id: "ses_1".to_string(),
+            runner_id: "local".to_string(),
+            ts: 0,
+            seq: None,
+            payload,
+        }
+    }
+
+    #[test]
+    fn events_of_hidden_messages_and_tool_calls_are_dropped() {
+        let delta = |message_id: &str, text: &str| {
+            event(EventPayload::StreamTextDelta {
+                message_id: message_id.to_string(),
+                delta: text.to_string(),
+                content_index: 0,
+            })
+        };
+        let tool_end = |tool_call_id: &str| {
+            event(EventPayload::ToolEnd {
+                tool_call_id: tool_call_id.to_string(),
+                name: "bash".to_string(),
+                output: serde_json::json!("token: xyz"),
+                is_error: false,
+                duration_ms: None,
+            })
+        };
+        let mut events = vec![
+            delta("m1", "AWS_SECRET=abc"),
+            delta("m2", "ok"),
+            tool_end("call_1"),
+            tool_end("call_2"),
+            event(EventPayload::Persisted { message_count: 2 }),
+        ];
+        let hidden = vec![mark("m1", Some("p1"))];
+
+        assert_eq!(
+            drop_hidden_events(&mut events, &hidden, &["call_1".to_string()]),
+            2
+        );
+
+        assert_eq!(events.len(), 3);
+        assert!(matches!(
+            &events[0].payload,
+            EventPayload::StreamTextDelta { message_id, .. } if message_id == "m2"
+        ));
+        assert!(matches!(
+            &events[1].payload,
+            EventPayload::ToolEnd { tool_call_id, .. } if tool_call_id == "call_2"
+        ));
+        assert!(matches!(events[2].payload, EventPayload::Persisted { .. }));
+    }
+}
@@ -0,0 +1,25 @@
+use serde::{Deserialize, Serialize};
+
+/// A message, or one part of it, that only the session owner can read.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct PrivateMessage {
+    pub id: String,
+    pub session_id: String,
+    pub message_id: String,
+    /// The private part; None when the whole message is private.
+    pub part_id: Option<String>,
+    /// Owner of the session, the only one who still sees the content.
+    pub owner_user_id: String,
+    /// Shared workspace of the session; None for personal sessions.
+    pub workspace_id: Option<String>,
+    pub created_at: String,
+}
+
+/// Request body for marking a message or part private.
+#[derive(Debug, Clone, Deserialize)]
+pub struct MarkPrivateRequest {
+    pub message_id: String,
+    /// Mark only this part of the message.
+    #[serde(default)]
+    pub part_id: Option<String>,
+}
@@ -0,0 +1,206 @@
+use anyhow::{Context, Result};
+use sqlx::FromRow;
+
+use crate::db::{self, DbPool, on_pool};
+
+use super::PrivateMessage;
+
+const PRIVATE_MESSAGE_COLUMNS: &str =
+    "id, session_id, message_id, part_id, owner_user_id, workspace_id, created_at";
+
+#[derive(Debug, Clone, FromRow)]
+struct PrivateMessageRow {
+    id: String,
+    session_id: String,
+    message_id: String,
+    part_id: String,
+    owner_user_id: String,
+    workspace_id: Option<String>,
+    created_at: String,
+}
+
+impl From<PrivateMessageRow> for PrivateMessage {
+    fn from(row: PrivateMessageRow) -> Self {
+        Self {
+            id: row.id,
+            session_id: row.session_id,
+            message_id: row.message_id,
+            // Whole messages are stored with an empty part so the unique
+            // key covers them.
+            part_id: Some(row.part_id).filter(|part| !part.is_empty()),
+            owner_user_id: row.owner_user_id,
+            workspace_id: row.workspace_id,
+            created_at: row.created_at,
+        }
+    }
+}
+
+/// A message or part to make private.
+#[derive(Debug, Clone)]
+pub struct NewPrivateMessage<'a> {
+    pub session_id: &'a str,
+    pub message_id: &'a str,
+    pub part_id: Option<&'a str>,
+    pub owner_user_id: &'a str,
+    pub workspace_id: Option<&'a str>,
+}
+
+#[derive(Debug, Clone)]
+pub struct PrivateMessageRepository {
+    pool: DbPool,
+}
+
+impl PrivateMessageRepository {
+    pub fn new(pool: DbPool) -> Self {
+        Self { pool }
+    }
+
+    fn generate_id() -> String {
+        format!("pm_{}", nanoid::nanoid!(12))
+    }
+
+    /// Make a message or part private. Marking it again returns the
+    /// existing mark.
+    pub async fn mark(&self, mark: &NewPrivateMessage<'_>) -> Result<PrivateMessage> {
+        let part_id = mark.part_id.unwrap_or_default();
+        on_pool!(&self.pool, |pool| sqlx::query(
+            "INSERT INTO private_messages \
+                 (id, session_id, message_id, part_id, owner_user_id, workspace_id, created_at) \
+             VALUES ($1, $2, $3, $4, $5, $6, $7) \
+             ON CONFLICT (session_id, message_id, part_id) DO NOTHING"
+        )
+        .bind(Self::generate_id())
+        .bind(mark.session_id)
+        .bind(mark.message_id)
+        .bind(part_id)
+        .bind(mark.owner_user_id)
+        .bind(mark.workspace_id)
+        .bind(db::now())
+        .execute(pool)
+        .await)
+        .context("insert private message")?;
+
+        let sql = format!(
+            "SELECT {PRIVATE_MESSAGE_COLUMNS} FROM private_messages \
+             WHERE session_id = $1 AND message_id = $2 AND part_id = $3"
+        );
+        let row = on_pool!(&self.pool, |pool| sqlx::query_as::<_, PrivateMessageRow>(
+            &sql
+        )
+        .bind(mark.session_id)
+        .bind(mark.message_id)
+        .bind(part_id)
+        .fetch_optional(pool)
+        .await)
+        .context("get private message")?;
+        row.map(Into::into)
+            .ok_or_else(|| anyhow::anyhow!("Private message not found after creation"))
+    }
+
+    pub async fn get(&self, id: &str) -> Result<Option<PrivateMessage>> {
+        let sql = format!("SELECT {PRIVATE_MESSAGE_COLUMNS} FROM private_messages WHERE id = $1");
+        let row = on_pool!(&self.pool, |pool| sqlx::query_as::<_, PrivateMessageRow>(
+            &sql
+        )
+        .bind(id)
+        .fetch_optional(pool)
+        .await)
+        .context("get private message")?;
+        Ok(row.map(Into::into))
+    }
+
+    /// Private messages and parts of a session, oldest first.
+    pub async fn list_for_session(&self, session_id: &str) -> Result<Vec<PrivateMessage>> {
+        let sql = format!(
+            "SELECT {PRIVATE_MESSAGE_COLUMNS} FROM private_messages \
+             WHERE session_id = $1 ORDER BY created_at, id"
+        );
+        let rows = on_pool!(&self.pool, |pool| sqlx::query_as::<_, PrivateMessageRow>(
+            &sql
+        )
+        .bind(session_id)
+        .fetch_all(pool)
+        .await)
+        .context("list private messages")?;
+        Ok(rows.into_iter().map(Into::into).collect())
+    }
+
+    /// Private messages and parts of a session that `viewer` may not read.
+    pub async fn hidden_from(&self, session_id: &str, viewer: &str) -> Result<Vec<PrivateMessage>> {
+        let sql = format!(
+            "SELECT {PRIVATE_MESSAGE_COLUMNS} FROM private_messages \
+             WHERE session_id = $1 AND owner_user_id <> $2"
+        );
+        let rows = on_pool!(&self.pool, |pool| sqlx::query_as::<_, PrivateMessageRow>(
+            &sql
+        )
+        .bind(session_id)
+        .bind(viewer)
+        .fetch_all(pool)
+        .await)
+        .context("list hidden messages")?;
+        Ok(rows.into_iter().map(Into::into).collect())
+    }
+
+    pub async fn count_for_session(&self, session_id: &str) -> Result<i64> {
+        on_pool!(&self.pool, |pool| sqlx::query_scalar(
+            "SELECT COUNT(*) FROM private_messages WHERE session_id = $1"
+        )
+        .bind(session_id)
+        .fetch_one(pool)
+        .await)
+        .context("count private messages")
+    }
+
+    pub async fn delete(&self, id: &str) -> Result<bool> {
+        let deleted = on_pool!(&self.pool, |pool| sqlx::query(
+            "DELETE FROM private_messages WHERE id = $1"
+        )
+        .bind(id)
+        .execute(pool)
+        .await
+        .map(|r| r.rows_affected()))
+        .context("delete private message")?;
+        Ok(deleted > 0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::Database;
+
+    #[tokio::test]
+    async fn marks_hide_messages_from_everyone_but_the_owner() {
+        let db = Database::in_memory().await.unwrap();
+        sqlx::query("INSERT INTO users (id, username, email, display_name) VALUES (?, ?, ?, ?)")
+            .bind("alice")
+            .bind("alice")
+            .bind("alice@example.com")
+            .bind("alice")
+            .execute(db.pool())
+            .await
+            .unwrap();
+        let repo = PrivateMessageRepository::new(db.shared().clone());
+        let mark = |part_id| NewPrivateMessage {
+            session_id: "ses_1",
+            message_id: "msg_1",
+            part_id,
+            owner_user_id: "alice",
+            workspace_id: None,
+        };
+
+        let whole = repo.mark(&mark(None)).await.unwrap();
+        assert_eq!(whole.part_id, None);
+        assert_eq!(repo.mark(&mark(None)).await.unwrap(), whole);
+        let part = repo.mark(&mark(Some("part_2"))).await.unwrap();
+        assert_eq!(part.part_id.as_deref(), Some("part_2"));
+        assert_eq!(repo.count_for_session("ses_1").await.unwrap(), 2);
+
+        assert!(repo.hidden_from("ses_1", "alice").await.unwrap().is_empty());
+        assert_eq!(repo.hidden_from("ses_1", "bob").await.unwrap().len(), 2);
+
+        assert!(repo.delete(&whole.id).await.unwrap());
+        assert_eq!(repo.list_for_session("ses_1").await.unwrap(), vec![part]);
+    }
+}
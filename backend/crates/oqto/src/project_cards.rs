@@ -0,0 +1,367 @@
+//! Project card data for the project grid (`GET /api/projects/cards`).
+//!
+//! A card sums up a workspace: title and description from its README, the
+//! primary language, when a file last changed and an optional thumbnail.
+//! Computing one walks the workspace, so cards are cached per workspace.
+//! An entry is recomputed when the README, `.oqto/workspace.toml` or the
+//! thumbnail changed, when a file watcher on the workspace reports a change
+//! (see [`ProjectCardService::invalidate`]), or after [`CARD_TTL`].
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{Duration, Instant, SystemTime};
+
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+
+use crate::markdown::summarize_readme;
+use crate::workspace::meta::load_workspace_meta;
+
+/// How long a card is served without a change being noticed.
+pub const CARD_TTL: Duration = Duration::from_secs(600);
+
+/// README files looked for, in order.
+const README_NAMES: &[&str] = &["README.md", "readme.md", "Readme.md", "README.markdown"];
+
+/// Thumbnails looked for when `workspace.toml` does not name one.
+const DEFAULT_THUMBNAILS: &[&str] = &[
+    ".oqto/thumbnail.png",
+    ".oqto/thumbnail.jpg",
+    ".oqto/thumbnail.jpeg",
+    ".oqto/thumbnail.webp",
+    ".oqto/thumbnail.svg",
+];
+
+/// Thumbnails are served up to this size.
+pub const MAX_THUMBNAIL_BYTES: u64 = 5 * 1024 * 1024;
+
+/// Files inspected for language and last activity; larger workspaces are
+/// sampled.
+const MAX_WALK_ENTRIES: usize = 5000;
+const MAX_WALK_DEPTH: usize = 8;
+
+/// Directories that hold dependencies or build output, not project files.
+const SKIPPED_DIRS: &[&str] = &[
+    "node_modules",
+    "target",
+    "vendor",
+    "dist",
+    "build",
+    "__pycache__",
+];
+
+/// Descriptions are cut to this many characters.
+const MAX_DESCRIPTION_CHARS: usize = 280;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ProjectCard {
+    pub path: String,
+    /// Display name from `workspace.toml`, or the directory name.
+    pub name: String,
+    /// First heading of the README.
+    pub title: Option<String>,
+    /// Opening paragraph of the README, as plain text.
+    pub description: Option<String>,
+    /// Language with the most source bytes.
+    pub language: Option<String>,
+    /// Newest modification time of a project file.
+    pub last_activity: Option<DateTime<Utc>>,
+    /// Thumbnail image, relative to the workspace.
+    pub thumbnail: Option<String>,
+}
+
+/// Modification times of the files a card is derived from directly.
+type Stamp = [Option<SystemTime>; 3];
+
+struct CachedCard {
+    card: ProjectCard,
+    stamp: Stamp,
+    computed: Instant,
+}
+
+/// Computes and caches [`ProjectCard`]s.
+#[derive(Default)]
+pub struct ProjectCardService {
+    cache: Mutex<HashMap<PathBuf, CachedCard>>,
+}
+
+impl ProjectCardService {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The card of the workspace at `path`.
+    pub async fn card(&self, path: &Path) -> Result<ProjectCard> {
+        let path = path.to_path_buf();
+        let stamp = {
+            let path = path.clone();
+            tokio::task::spawn_blocking(move || stamp(&path)).await?
+        };
+        if let Some(cached) = self.cache.lock().unwrap().get(&path)
+            && cached.stamp == stamp
+            && cached.computed.elapsed() < CARD_TTL
+        {
+            return Ok(cached.card.clone());
+        }
+
+        let card = {
+            let path = path.clone();
+            tokio::task::spawn_blocking(move || compute(&path)).await?
+        };
+        self.cache.lock().unwrap().insert(
+            path,
+            CachedCard {
+                card: card.clone(),
+                stamp,
+                computed: Instant::now(),
+            },
+        );
+        Ok(card)
+    }
+
+    /// Drop the cached card of the workspace at `path`, e.g. because a file
+    /// in it changed.
+    pub fn invalidate(&self, path: &Path) {
+        let mut cache = self.cache.lock().unwrap();
+        if cache.remove(path).is_none()
+            && let Ok(canonical) = path.canonicalize()
+        {
+            cache.remove(&canonical);
+        }
+    }
+}
+
+/// The thumbnail of a workspace: the image named in `workspace.toml`, or the
+/// first of [`DEFAULT_THUMBNAILS`] present. Only images inside the workspace
+/// qualify.
+pub fn thumbnail(workspace: &Path) -> Option<String> {
+    let designated = load_workspace_meta(workspace)
+        .and_then(|meta| meta.thumbnail)
+        .map(|t| t.trim().trim_start_matches("./").to_string());
+    let candidates = designated
+        .into_iter()
+        .chain(DEFAULT_THUMBNAILS.iter().map(|t| t.to_string()));
+    let root = workspace.canonicalize().ok()?;
+    for candidate in candidates {
+        if thumbnail_content_type(&candidate).is_none() {
+            continue;
+        }
+        let Ok(canonical) = workspace.join(&candidate).canonicalize() else {
+            continue;
+        };
+        if canonical.starts_with(&root) && canonical.is_file() {
+            return Some(candidate);
+        }
+    }
+    None
+}
+
+/// Content type of a thumbnail, None for files that are not images.
+pub fn thumbnail_content_type(path: &str) -> Option<&'static str> {
+    let ext = Path::new(path).extension()?.to_str()?.to_ascii_lowercase();
+    match ext.as_str() {
+        "png" => Some("image/png"),
+        "jpg" | "jpeg" => Some("image/jpeg"),
+        "webp" => Some("image/webp"),
+        "gif" => Some("image/gif"),
+        "svg" => Some("image/svg+xml"),
+        _ => None,
+    }
+}
+
+fn readme_path(workspace: &Path) -> Option<PathBuf> {
+    README_NAMES
+        .iter()
+        .map(|name| workspace.join(name))
+        .find(|path| path.is_file())
+}
+
+fn mtime(path: &Path) -> Option<SystemTime> {
+    std::fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+fn stamp(workspace: &Path) -> Stamp {
+    [
+        readme_path(workspace).and_then(|p| mtime(&p)),
+        mtime(&crate::workspace::meta::workspace_meta_path(workspace)),
+        thumbnail(workspace).and_then(|t| mtime(&workspace.join(t))),
+    ]
+}
+
+fn compute(workspace: &Path) -> ProjectCard {
+    let dir_name = workspace
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let name = crate::workspace::meta::workspace_display_name(workspace).unwrap_or(dir_name);
+
+    let summary = readme_path(workspace)
+        .and_then(|path| std::fs::read_to_string(path).ok())
+        .map(|content| summarize_readme(&content))
+        .unwrap_or_default();
+    let description = summary
+        .description
+        .map(|d| truncate(&d, MAX_DESCRIPTION_CHARS));
+
+    let (language, last_activity) = scan(workspace);
+
+    ProjectCard {
+        path: workspace.to_string_lossy().to_string(),
+        name,
+        title: summary.title,
+        description,
+        language,
+        last_activity: last_activity.map(DateTime::<Utc>::from),
+        thumbnail: thumbnail(workspace),
+    }
+}
+
+fn truncate(text: &str, max_chars: usize) -> String {
+    match text.char_indices().nth(max_chars) {
+        Some((end, _)) => format!("{}…", text[..end].trim_end()),
+        None => text.to_string(),
+    }
+}
+
+/// Walk the workspace for the language with the most bytes and the newest
+/// modification time. Hidden entries and [`SKIPPED_DIRS`] are left out.
+fn scan(workspace: &Path) -> (Option<String>, Option<SystemTime>) {
+    let mut bytes_by_language: HashMap<&'static str, u64> = HashMap::new();
+    let mut newest: Option<SystemTime> = None;
+    let mut seen = 0;
+    let mut stack = vec![(workspace.to_path_buf(), 0)];
+
+    while let Some((dir, depth)) = stack.pop() {
+        let Ok(entries) = std::fs::read_dir(&dir) else {
+            continue;
+        };
+        for entry in entries.flatten() {
+            seen += 1;
+            if seen > MAX_WALK_ENTRIES {
+                stack.clear();
+                break;
+            }
+            let name = entry.file_name();
+            let name = name.to_string_lossy();
+            if name.starts_with('.') {
+                continue;
+            }
+            let Ok(file_type) = entry.file_type() else {
+                continue;
+            };
+            if file_type.is_dir() {
+                if depth < MAX_WALK_DEPTH && !SKIPPED_DIRS.contains(&name.as_ref()) {
+                    stack.push((entry.path(), depth + 1));
+                }
+                continue;
+            }
+            if !file_type.is_file() {
+                continue;
+            }
+            let Ok(metadata) = entry.metadata() else {
+                continue;
+            };
+            if let Ok(modified) = metadata.modified() {
+                newest = newest.max(Some(modified));
+            }
+            if let Some(language) = language_for(&name) {
+                *bytes_by_language.entry(language).or_default() += metadata.len();
+            }
+        }
+    }
+
+    let language = bytes_by_language
+        .into_iter()
+        .max_by(|a, b| a.1.cmp(&b.1).then(b.0.cmp(a.0)))
+        .map(|(language, _)| language.to_string());
+    (language, newest)
+}
+
+/// Programming language of a file, by extension.
+fn language_for(file_name: &str) -> Option<&'static str> {
+    let ext = Path::new(file_name).extension()?.to_str()?;
+    Some(match ext {
+        "rs" => "Rust",
+        "ts" | "tsx" | "mts" | "cts" => "TypeScript",
+        "js" | "jsx" | "mjs" | "cjs" => "JavaScript",
+        "py" => "Python",
+        "go" => "Go",
+        "java" => "Java",
+        "kt" | "kts" => "Kotlin",
+        "swift" => "Swift",
+        "c" | "h" => "C",
+        "cc" | "cpp" | "cxx" | "hpp" | "hh" => "C++",
+        "cs" => "C#",
+        "rb" => "Ruby",
+        "php" => "PHP",
+        "sh" | "bash" | "zsh" => "Shell",
+        "lua" => "Lua",
+        "zig" => "Zig",
+        "ex" | "exs" => "Elixir",
+        "hs" => "Haskell",
+        "scala" => "Scala",
+        "dart" => "Dart",
+        "vue" => "Vue",
+        "svelte" => "Svelte",
+        "nix" => "Nix",
+        "typ" => "Typst",
+        "tex" => "TeX",
+        _ => return None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_card_from_workspace() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        std::fs::write(root.join("README.md"), "# Demo\n\nA small demo.\n").unwrap();
+        std::fs::create_dir_all(root.join("src")).unwrap();
+        std::fs::write(root.join("src/main.rs"), "fn main() {}\n".repeat(10)).unwrap();
+        std::fs::write(root.join("build.sh"), "echo hi\n").unwrap();
+        std::fs::create_dir_all(root.join("node_modules/dep")).unwrap();
+        std::fs::write(root.join("node_modules/dep/index.js"), "x".repeat(10_000)).unwrap();
+        std::fs::create_dir_all(root.join(".oqto")).unwrap();
+        std::fs::write(root.join(".oqto/thumbnail.png"), b"png").unwrap();
+
+        let service = ProjectCardService::new();
+        let card = service.card(root).await.unwrap();
+        assert_eq!(card.title.as_deref(), Some("Demo"));
+        assert_eq!(card.description.as_deref(), Some("A small demo."));
+        assert_eq!(card.language.as_deref(), Some("Rust"));
+        assert!(card.last_activity.is_some());
+        assert_eq!(card.thumbnail.as_deref(), Some(".oqto/thumbnail.png"));
+
+        // A designated thumbnail outside the workspace is ignored.
+        std::fs::write(
+            root.join(".oqto/workspace.toml"),
+            "thumbnail = \"../elsewhere.png\"\n",
+        )
+        .unwrap();
+        assert_eq!(thumbnail(root).as_deref(), Some(".oqto/thumbnail.png"));
+
+        // README edits are picked up without waiting for the TTL.
+        std::fs::write(root.join("README.md"), "# Renamed\n").unwrap();
+        let later = SystemTime::now() + Duration::from_secs(5);
+        std::fs::File::options()
+            .write(true)
+            .open(root.join("README.md"))
+            .unwrap()
+            .set_modified(later)
+            .unwrap();
+        let card = service.card(root).await.unwrap();
+        assert_eq!(card.title.as_deref(), Some("Renamed"));
+        assert_eq!(card.description, None);
+    }
+
+    #[test]
+    fn test_truncate_description() {
+        assert_eq!(truncate("short", 10), "short");
+        assert_eq!(truncate("a sentence that is long", 10), "a sentence…");
+    }
+}
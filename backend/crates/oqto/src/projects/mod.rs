@@ -1,3 +1,9 @@
+mod template_vars;
+
+pub use template_vars::{
+    TemplateVariable, read_variables, render_template, resolve_values, sample_values,
+};
+
 use std::path::{Path, PathBuf};
 
 use anyhow::{Context, Result};
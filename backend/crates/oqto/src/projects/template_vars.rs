@@ -0,0 +1,290 @@
+//! Variables of project templates.
+//!
+//! A template declares them in its `template.json`:
+//!
+//! ```json
+//! { "variables": [
+//!     { "name": "project_name", "label": "Project name" },
+//!     { "name": "license", "default": "MIT", "choices": ["MIT", "Apache-2.0"] }
+//! ] }
+//! ```
+//!
+//! Creating a project renders the template into a staging directory first:
+//! `{{name}}` in text files and in file and directory names is replaced with
+//! the value. Only declared names are replaced, so other `{{ ... }}` text
+//! (GitHub Actions expressions, Handlebars views) is left alone.
+
+use std::collections::HashMap;
+use std::path::Path;
+
+use anyhow::{Context, Result, bail};
+use serde::{Deserialize, Serialize};
+
+/// Largest file that is rendered; bigger ones are copied as they are.
+const MAX_RENDERED_FILE_BYTES: u64 = 1024 * 1024;
+
+/// Longest accepted value.
+const MAX_VALUE_LEN: usize = 500;
+
+/// A value asked for when creating a project from the template.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TemplateVariable {
+    /// Referenced as `{{name}}`; letters, digits and `_`.
+    pub name: String,
+    /// What the UI asks for (default: the name).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub label: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    /// Value used when none is given. Without a default the variable is
+    /// required.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub default: Option<String>,
+    /// Allowed values; any value when empty.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub choices: Vec<String>,
+}
+
+/// The template's declared variables; empty when it declares none.
+pub fn read_variables(template_dir: &Path) -> Result<Vec<TemplateVariable>> {
+    let path = template_dir.join("template.json");
+    let contents = match std::fs::read_to_string(&path) {
+        Ok(contents) => contents,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e).with_context(|| format!("reading {}", path.display())),
+    };
+    let value: serde_json::Value =
+        serde_json::from_str(&contents).with_context(|| format!("parsing {}", path.display()))?;
+    let Some(variables) = value.get("variables") else {
+        return Ok(Vec::new());
+    };
+    let variables: Vec<TemplateVariable> = serde_json::from_value(variables.clone())
+        .with_context(|| format!("invalid variables in {}", path.display()))?;
+    if let Some(bad) = variables.iter().find(|v| !is_identifier(&v.name)) {
+        bail!("invalid variable name '{}' in {}", bad.name, path.display());
+    }
+    Ok(variables)
+}
+
+/// The value of every variable: the given one or the default. Returns a
+/// message for the caller naming missing or invalid values.
+pub fn resolve_values(
+    variables: &[TemplateVariable],
+    given: &HashMap<String, String>,
+) -> Result<HashMap<String, String>, String> {
+    if let Some(name) = given
+        .keys()
+        .find(|name| !variables.iter().any(|v| &v.name == *name))
+    {
+        return Err(format!("template declares no variable '{name}'"));
+    }
+    let mut values = HashMap::new();
+    let mut missing = Vec::new();
+    for variable in variables {
+        let value = given
+            .get(&variable.name)
+            .map(|v| v.trim())
+            .filter(|v| !v.is_empty())
+            .or(variable.default.as_deref());
+        let Some(value) = value else {
+            missing.push(variable.name.as_str());
+            continue;
+        };
+        if value.len() > MAX_VALUE_LEN {
+            return Err(format!(
+                "value of '{}' is longer than {MAX_VALUE_LEN} characters",
+                variable.name
+            ));
+        }
+        if !variable.choices.is_empty() && !variable.choices.iter().any(|c| c == value) {
+            return Err(format!(
+                "value of '{}' must be one of: {}",
+                variable.name,
+                variable.choices.join(", ")
+            ));
+        }
+        values.insert(variable.name.clone(), value.to_string());
+    }
+    if !missing.is_empty() {
+        return Err(format!(
+            "missing values for template variables: {}",
+            missing.join(", ")
+        ));
+    }
+    Ok(values)
+}
+
+/// Stand-in values for rendering without asking: the default, else the
+/// first choice, else the variable's name.
+pub fn sample_values(variables: &[TemplateVariable]) -> HashMap<String, String> {
+    variables
+        .iter()
+        .map(|v| {
+            let value = v
+                .default
+                .clone()
+                .or_else(|| v.choices.first().cloned())
+                .unwrap_or_else(|| v.name.clone());
+            (v.name.clone(), value)
+        })
+        .collect()
+}
+
+/// Copy `src` to `dest` (which must not exist), rendering file contents and
+/// names. `.git` is skipped, binary and large files are copied unchanged.
+pub fn render_template(src: &Path, dest: &Path, values: &HashMap<String, String>) -> Result<()> {
+    std::fs::create_dir(dest).with_context(|| format!("creating {}", dest.display()))?;
+    for entry in std::fs::read_dir(src).with_context(|| format!("reading {}", src.display()))? {
+        let entry = entry?;
+        let file_name = entry.file_name();
+        if file_name == ".git" {
+            continue;
+        }
+        let src_path = entry.path();
+        let name = file_name.to_string_lossy();
+        let rendered_name = substitute(&name, values);
+        if rendered_name.is_empty()
+            || rendered_name == "."
+            || rendered_name == ".."
+            || rendered_name.contains(['/', '\\', '\0'])
+        {
+            bail!("'{name}' renders to the invalid file name '{rendered_name}'");
+        }
+        let dest_path = dest.join(&rendered_name);
+        let file_type = entry.file_type()?;
+        if file_type.is_dir() {
+            render_template(&src_path, &dest_path, values)?;
+        } else if file_type.is_file() {
+            render_file(&src_path, &dest_path, values)?;
+        }
+    }
+    Ok(())
+}
+
+fn render_file(src: &Path, dest: &Path, values: &HashMap<String, String>) -> Result<()> {
+    let metadata = std::fs::metadata(src)?;
+    let text = if metadata.len() <= MAX_RENDERED_FILE_BYTES {
+        String::from_utf8(std::fs::read(src)?).ok()
+    } else {
+        None
+    };
+    match text {
+        Some(text) => std::fs::write(dest, substitute(&text, values))?,
+        None => {
+            std::fs::copy(src, dest)?;
+        }
+    }
+    // Keep scripts executable.
+    std::fs::set_permissions(dest, metadata.permissions())
+        .with_context(|| format!("writing {}", dest.display()))
+}
+
+/// Replace `{{name}}` placeholders of the given names; anything else is
+/// kept as is.
+fn substitute(text: &str, values: &HashMap<String, String>) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find("{{") {
+        let Some(len) = rest[start + 2..].find("}}") else {
+            break;
+        };
+        let end = start + 4 + len;
+        let name = rest[start + 2..start + 2 + len].trim();
+        out.push_str(&rest[..start]);
+        match values.get(name) {
+            Some(value) => out.push_str(value),
+            None => out.push_str(&rest[start..end]),
+        }
+        rest = &rest[end..];
+    }
+    out.push_str(rest);
+    out
+}
+
+fn is_identifier(name: &str) -> bool {
+    !name.is_empty() && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn variable(name: &str, default: Option<&str>, choices: &[&str]) -> TemplateVariable {
+        TemplateVariable {
+            name: name.to_string(),
+            label: None,
+            description: None,
+            default: default.map(str::to_string),
+            choices: choices.iter().map(|c| c.to_string()).collect(),
+        }
+    }
+
+    #[test]
+    fn values_come_from_the_request_or_the_defaults() {
+        let variables = vec![
+            variable("project_name", None, &[]),
+            variable("license", Some("MIT"), &["MIT", "Apache-2.0"]),
+        ];
+        let given = |pairs: &[(&str, &str)]| -> HashMap<String, String> {
+            pairs
+                .iter()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect()
+        };
+
+        let err = resolve_values(&variables, &given(&[])).unwrap_err();
+        assert!(err.contains("project_name"), "{err}");
+        let values = resolve_values(&variables, &given(&[("project_name", "demo")])).unwrap();
+        assert_eq!(values["license"], "MIT");
+        assert!(
+            resolve_values(
+                &variables,
+                &given(&[("project_name", "demo"), ("license", "GPL")])
+            )
+            .is_err()
+        );
+        assert!(
+            resolve_values(&variables, &given(&[("project_name", "demo"), ("x", "1")])).is_err()
+        );
+        assert_eq!(sample_values(&variables)["project_name"], "project_name");
+    }
+
+    #[test]
+    fn templates_render_contents_and_names() {
+        let temp = tempfile::tempdir().unwrap();
+        let src = temp.path().join("template");
+        std::fs::create_dir_all(src.join("src/{{project_name}}")).unwrap();
+        std::fs::create_dir_all(src.join(".git")).unwrap();
+        std::fs::write(
+            src.join("README.md"),
+            "# {{ project_name }}\nrun: ${{ github.sha }}\n",
+        )
+        .unwrap();
+        std::fs::write(
+            src.join("src/{{project_name}}/lib.rs"),
+            "// {{project_name}}",
+        )
+        .unwrap();
+        std::fs::write(src.join("logo.bin"), [0xff, 0xfe, b'{', b'{']).unwrap();
+        let values = HashMap::from([("project_name".to_string(), "demo".to_string())]);
+
+        let dest = temp.path().join("project");
+        render_template(&src, &dest, &values).unwrap();
+        assert_eq!(
+            std::fs::read_to_string(dest.join("README.md")).unwrap(),
+            "# demo\nrun: ${{ github.sha }}\n"
+        );
+        assert_eq!(
+            std::fs::read_to_string(dest.join("src/demo/lib.rs")).unwrap(),
+            "// demo"
+        );
+        assert_eq!(
+            std::fs::read(dest.join("logo.bin")).unwrap(),
+            [0xff, 0xfe, b'{', b'{']
+        );
+        assert!(!dest.join(".git").exists());
+
+        let escaping = HashMap::from([("project_name".to_string(), "../x".to_string())]);
+        assert!(render_template(&src, &temp.path().join("other"), &escaping).is_err());
+    }
+}
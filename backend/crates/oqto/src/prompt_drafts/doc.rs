@@ -0,0 +1,294 @@
+//! Replicated prompt text: an RGA sequence in which every character has a
+//! unique id, so inserts and deletes from several clients merge the same way
+//! in any order.
+
+use anyhow::{Result, bail};
+use serde::{Deserialize, Serialize};
+
+/// Visible characters a draft may hold.
+pub const MAX_DRAFT_CHARS: usize = 64 * 1024;
+/// Characters a draft may hold including deleted ones.
+const MAX_DRAFT_ITEMS: usize = 4 * MAX_DRAFT_CHARS;
+
+/// Id of one character: the client that typed it and a Lamport clock value.
+/// Clients pick `seq` above every `seq` they have seen.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct CharId {
+    pub client: String,
+    pub seq: u64,
+}
+
+impl CharId {
+    /// Whether `self` was typed after `other` (ties broken by client).
+    fn newer_than(&self, other: &CharId) -> bool {
+        (self.seq, &self.client) > (other.seq, &other.client)
+    }
+}
+
+/// One edit of a draft.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "op", rename_all = "snake_case")]
+pub enum DraftOp {
+    /// Insert `text` after the character `after` (None: at the start). Its
+    /// characters get the ids `id.seq`, `id.seq + 1`, ... of `id.client`.
+    Insert {
+        id: CharId,
+        #[serde(default)]
+        after: Option<CharId>,
+        text: String,
+    },
+    /// Delete the characters `start.seq..start.seq + len` of `start.client`.
+    Delete { start: CharId, len: u64 },
+}
+
+/// Consecutive characters of one client, as stored and sent to clients.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DraftRun {
+    /// Id of the first character; the others follow in `seq`.
+    pub id: CharId,
+    pub text: String,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub deleted: bool,
+}
+
+/// Full state of a draft, deleted characters included so that concurrent
+/// edits referring to them still apply.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DraftState {
+    /// Number of edits applied.
+    pub version: u64,
+    pub runs: Vec<DraftRun>,
+}
+
+#[derive(Debug, Clone)]
+struct DraftChar {
+    id: CharId,
+    ch: char,
+    deleted: bool,
+}
+
+/// A draft being edited.
+#[derive(Debug, Clone, Default)]
+pub struct DraftDoc {
+    chars: Vec<DraftChar>,
+    version: u64,
+}
+
+impl DraftDoc {
+    pub fn from_state(state: DraftState) -> Self {
+        let chars = state
+            .runs
+            .into_iter()
+            .flat_map(|run| {
+                let DraftRun { id, text, deleted } = run;
+                text.chars()
+                    .enumerate()
+                    .map(|(i, ch)| DraftChar {
+                        id: CharId {
+                            client: id.client.clone(),
+                            seq: id.seq + i as u64,
+                        },
+                        ch,
+                        deleted,
+                    })
+                    .collect::<Vec<_>>()
+            })
+            .collect();
+        Self {
+            chars,
+            version: state.version,
+        }
+    }
+
+    pub fn state(&self) -> DraftState {
+        let mut runs: Vec<DraftRun> = Vec::new();
+        let mut next: Option<CharId> = None;
+        for c in &self.chars {
+            if let Some(run) = runs.last_mut()
+                && next.as_ref() == Some(&c.id)
+                && run.deleted == c.deleted
+            {
+                run.text.push(c.ch);
+            } else {
+                runs.push(DraftRun {
+                    id: c.id.clone(),
+                    text: c.ch.to_string(),
+                    deleted: c.deleted,
+                });
+            }
+            next = Some(CharId {
+                client: c.id.client.clone(),
+                seq: c.id.seq + 1,
+            });
+        }
+        DraftState {
+            version: self.version,
+            runs,
+        }
+    }
+
+    pub fn version(&self) -> u64 {
+        self.version
+    }
+
+    /// The visible text.
+    pub fn text(&self) -> String {
+        self.chars
+            .iter()
+            .filter(|c| !c.deleted)
+            .map(|c| c.ch)
+            .collect()
+    }
+
+    /// Apply edits. Edits already applied are skipped; on error nothing is
+    /// applied and the client should reload the draft.
+    pub fn apply(&mut self, ops: &[DraftOp]) -> Result<()> {
+        let mut next = self.clone();
+        for op in ops {
+            next.apply_one(op)?;
+        }
+        if next.chars.len() > MAX_DRAFT_ITEMS
+            || next.chars.iter().filter(|c| !c.deleted).count() > MAX_DRAFT_CHARS
+        {
+            bail!("Draft is too long");
+        }
+        next.version += 1;
+        *self = next;
+        Ok(())
+    }
+
+    fn apply_one(&mut self, op: &DraftOp) -> Result<()> {
+        match op {
+            DraftOp::Insert { id, after, text } => {
+                let mut after = after.clone();
+                for (i, ch) in text.chars().enumerate() {
+                    let char_id = CharId {
+                        client: id.client.clone(),
+                        seq: id.seq + i as u64,
+                    };
+                    self.insert(&char_id, after.as_ref(), ch)?;
+                    after = Some(char_id);
+                }
+            }
+            DraftOp::Delete { start, len } => {
+                let end = start.seq.saturating_add(*len);
+                for c in &mut self.chars {
+                    if c.id.client == start.client && (start.seq..end).contains(&c.id.seq) {
+                        c.deleted = true;
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn insert(&mut self, id: &CharId, after: Option<&CharId>, ch: char) -> Result<()> {
+        if self.index_of(id).is_some() {
+            return Ok(());
+        }
+        let mut pos = match after {
+            None => 0,
+            Some(after) => match self.index_of(after) {
+                Some(index) => index + 1,
+                None => bail!("Unknown draft position {}:{}", after.client, after.seq),
+            },
+        };
+        // Concurrent inserts at the same position: newer ones come first.
+        while pos < self.chars.len() && self.chars[pos].id.newer_than(id) {
+            pos += 1;
+        }
+        self.chars.insert(
+            pos,
+            DraftChar {
+                id: id.clone(),
+                ch,
+                deleted: false,
+            },
+        );
+        Ok(())
+    }
+
+    fn index_of(&self, id: &CharId) -> Option<usize> {
+        self.chars.iter().position(|c| &c.id == id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn id(client: &str, seq: u64) -> CharId {
+        CharId {
+            client: client.to_string(),
+            seq,
+        }
+    }
+
+    fn insert(client: &str, seq: u64, after: Option<CharId>, text: &str) -> DraftOp {
+        DraftOp::Insert {
+            id: id(client, seq),
+            after,
+            text: text.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_concurrent_edits_converge() {
+        let mut base = DraftDoc::default();
+        base.apply(&[insert("a", 1, None, "helo")]).unwrap();
+
+        // Desktop fixes the typo while mobile appends.
+        let desktop = vec![insert("a", 5, Some(id("a", 3)), "l")];
+        let mobile = vec![
+            insert("b", 5, Some(id("a", 4)), " world"),
+            DraftOp::Delete {
+                start: id("a", 1),
+                len: 1,
+            },
+            insert("b", 11, None, "H"),
+        ];
+
+        let mut one = base.clone();
+        one.apply(&desktop).unwrap();
+        one.apply(&mobile).unwrap();
+        let mut two = base.clone();
+        two.apply(&mobile).unwrap();
+        two.apply(&desktop).unwrap();
+
+        assert_eq!(one.text(), "Hello world");
+        assert_eq!(one.state().runs, two.state().runs);
+
+        // Replayed edits are no-ops.
+        two.apply(&desktop).unwrap();
+        assert_eq!(two.text(), "Hello world");
+    }
+
+    #[test]
+    fn test_state_round_trip_and_errors() {
+        let mut doc = DraftDoc::default();
+        doc.apply(&[
+            insert("a", 1, None, "abc"),
+            DraftOp::Delete {
+                start: id("a", 2),
+                len: 1,
+            },
+        ])
+        .unwrap();
+        let state = doc.state();
+        assert_eq!(state.runs.len(), 3);
+        assert_eq!(state.version, 1);
+
+        let mut restored = DraftDoc::from_state(state.clone());
+        assert_eq!(restored.text(), "ac");
+        assert_eq!(restored.state(), state);
+
+        // An edit relative to an unknown character is rejected as a whole.
+        let err = restored.apply(&[
+            insert("b", 4, Some(id("a", 3)), "d"),
+            insert("b", 5, Some(id("z", 1)), "e"),
+        ]);
+        assert!(err.is_err());
+        assert_eq!(restored.text(), "ac");
+        assert_eq!(restored.version(), 1);
+    }
+}
@@ -0,0 +1,16 @@
+//! Prompt drafts shared between a user's devices.
+//!
+//! While a prompt is being written, every connected client of the user
+//! (desktop, mobile, another tab) edits the same draft of the session. Edits
+//! are small CRDT operations (see [`doc`]) sent over the `draft` WebSocket
+//! channel; the server merges them into the stored draft and relays them to
+//! the user's other connections, so switching devices mid-sentence keeps the
+//! text. Sending stays a single `prompt` command, which clears the draft.
+
+pub mod doc;
+mod repository;
+mod service;
+
+pub use doc::{DraftDoc, DraftOp, DraftState};
+pub use repository::PromptDraftRepository;
+pub use service::PromptDraftService;
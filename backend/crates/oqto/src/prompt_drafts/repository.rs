@@ -0,0 +1,104 @@
+use anyhow::{Context, Result};
+
+use crate::db::{self, DbPool, on_pool};
+
+use super::DraftState;
+
+#[derive(Debug, Clone)]
+pub struct PromptDraftRepository {
+    pool: DbPool,
+}
+
+impl PromptDraftRepository {
+    pub fn new(pool: DbPool) -> Self {
+        Self { pool }
+    }
+
+    /// The stored draft of a user's session, if any.
+    pub async fn get(&self, user_id: &str, session_id: &str) -> Result<Option<DraftState>> {
+        let state: Option<String> = on_pool!(&self.pool, |pool| sqlx::query_scalar(
+            "SELECT state FROM prompt_drafts WHERE user_id = $1 AND session_id = $2"
+        )
+        .bind(user_id)
+        .bind(session_id)
+        .fetch_optional(pool)
+        .await)
+        .context("get prompt draft")?;
+        state
+            .map(|state| serde_json::from_str(&state).context("parse prompt draft"))
+            .transpose()
+    }
+
+    pub async fn upsert(&self, user_id: &str, session_id: &str, state: &DraftState) -> Result<()> {
+        let state = serde_json::to_string(state).context("serialize prompt draft")?;
+        on_pool!(&self.pool, |pool| sqlx::query(
+            r#"INSERT INTO prompt_drafts (user_id, session_id, state, updated_at)
+               VALUES ($1, $2, $3, $4)
+               ON CONFLICT (user_id, session_id) DO UPDATE SET
+                   state = excluded.state,
+                   updated_at = excluded.updated_at"#
+        )
+        .bind(user_id)
+        .bind(session_id)
+        .bind(&state)
+        .bind(db::now())
+        .execute(pool)
+        .await)
+        .context("upsert prompt draft")?;
+        Ok(())
+    }
+
+    pub async fn delete(&self, user_id: &str, session_id: &str) -> Result<bool> {
+        let deleted = on_pool!(&self.pool, |pool| sqlx::query(
+            "DELETE FROM prompt_drafts WHERE user_id = $1 AND session_id = $2"
+        )
+        .bind(user_id)
+        .bind(session_id)
+        .execute(pool)
+        .await
+        .map(|r| r.rows_affected()))
+        .context("delete prompt draft")?;
+        Ok(deleted > 0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::Database;
+    use crate::prompt_drafts::doc::CharId;
+    use crate::prompt_drafts::{DraftDoc, DraftOp};
+
+    #[tokio::test]
+    async fn test_draft_round_trip() {
+        let db = Database::in_memory().await.unwrap();
+        sqlx::query("INSERT INTO users (id, username, email, display_name) VALUES (?, ?, ?, ?)")
+            .bind("alice")
+            .bind("alice")
+            .bind("alice@example.com")
+            .bind("alice")
+            .execute(db.pool())
+            .await
+            .unwrap();
+        let repo = PromptDraftRepository::new(db.shared().clone());
+        assert!(repo.get("alice", "ses_1").await.unwrap().is_none());
+
+        let mut doc = DraftDoc::default();
+        doc.apply(&[DraftOp::Insert {
+            id: CharId {
+                client: "desktop".to_string(),
+                seq: 1,
+            },
+            after: None,
+            text: "fix the tests".to_string(),
+        }])
+        .unwrap();
+        repo.upsert("alice", "ses_1", &doc.state()).await.unwrap();
+        repo.upsert("alice", "ses_1", &doc.state()).await.unwrap();
+
+        let stored = repo.get("alice", "ses_1").await.unwrap().unwrap();
+        assert_eq!(DraftDoc::from_state(stored).text(), "fix the tests");
+        assert!(repo.delete("alice", "ses_1").await.unwrap());
+        assert!(!repo.delete("alice", "ses_1").await.unwrap());
+    }
+}
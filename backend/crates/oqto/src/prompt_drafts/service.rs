@@ -0,0 +1,51 @@
+use anyhow::Result;
+use tokio::sync::Mutex;
+
+use super::{DraftDoc, DraftOp, PromptDraftRepository};
+
+/// Applies draft edits on top of the stored drafts.
+#[derive(Debug)]
+pub struct PromptDraftService {
+    repo: PromptDraftRepository,
+    /// Serializes read-modify-write of drafts.
+    lock: Mutex<()>,
+}
+
+impl PromptDraftService {
+    pub fn new(repo: PromptDraftRepository) -> Self {
+        Self {
+            repo,
+            lock: Mutex::new(()),
+        }
+    }
+
+    /// The current draft of a user's session (empty if none).
+    pub async fn get(&self, user_id: &str, session_id: &str) -> Result<DraftDoc> {
+        Ok(self
+            .repo
+            .get(user_id, session_id)
+            .await?
+            .map(DraftDoc::from_state)
+            .unwrap_or_default())
+    }
+
+    /// Apply edits and store the result.
+    pub async fn apply(
+        &self,
+        user_id: &str,
+        session_id: &str,
+        ops: &[DraftOp],
+    ) -> Result<DraftDoc> {
+        let _guard = self.lock.lock().await;
+        let mut doc = self.get(user_id, session_id).await?;
+        doc.apply(ops)?;
+        self.repo.upsert(user_id, session_id, &doc.state()).await?;
+        Ok(doc)
+    }
+
+    /// Drop a draft, e.g. once it was sent. Returns whether there was one.
+    pub async fn clear(&self, user_id: &str, session_id: &str) -> Result<bool> {
+        let _guard = self.lock.lock().await;
+        self.repo.delete(user_id, session_id).await
+    }
+}
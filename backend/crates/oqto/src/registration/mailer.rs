@@ -0,0 +1,49 @@
+//! Delivery of verification emails.
+
+use anyhow::Result;
+use async_trait::async_trait;
+use tracing::info;
+
+use crate::outbox::{EmailSenderConfig, OutboxContent, format_email, sendmail};
+
+/// Sends the emails of the registration flow.
+#[async_trait]
+pub trait Mailer: Send + Sync {
+    async fn send(&self, to: &str, subject: &str, body: &str) -> Result<()>;
+}
+
+/// Sends through the host's sendmail-compatible MTA.
+pub struct SendmailMailer {
+    config: EmailSenderConfig,
+}
+
+impl SendmailMailer {
+    pub fn new(config: EmailSenderConfig) -> Self {
+        Self { config }
+    }
+}
+
+#[async_trait]
+impl Mailer for SendmailMailer {
+    async fn send(&self, to: &str, subject: &str, body: &str) -> Result<()> {
+        let content = OutboxContent {
+            recipients: vec![to.to_string()],
+            cc: Vec::new(),
+            subject: Some(subject.to_string()),
+            body: body.to_string(),
+        };
+        let (_, rfc822) = format_email(&self.config.from, &content, &[])?;
+        sendmail(&self.config, &content.recipients, &rfc822).await
+    }
+}
+
+/// Logs emails instead of sending them, for development.
+pub struct LogMailer;
+
+#[async_trait]
+impl Mailer for LogMailer {
+    async fn send(&self, to: &str, subject: &str, body: &str) -> Result<()> {
+        info!(to = %to, subject = %subject, "Registration email (not sent):\n{body}");
+        Ok(())
+    }
+}
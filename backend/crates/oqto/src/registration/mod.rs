@@ -0,0 +1,461 @@
+//! Open self-service registration.
+//!
+//! With `[registration] open = true`, `POST /api/auth/register` accepts
+//! requests without an invite code for deployments that cannot hand codes
+//! out. Such registrations are limited per client IP and per day, can be
+//! restricted to email domains, and are recorded in `registrations`. Until
+//! the user follows the link in the verification email they are quarantined
+//! to `quarantine.max_concurrent_sessions`; with `require_approval` the
+//! account stays disabled until an admin approves it from the queue.
+//! Registrations with an invite code bypass all of this.
+
+mod mailer;
+mod models;
+mod repository;
+
+pub use mailer::{LogMailer, Mailer, SendmailMailer};
+pub use models::{
+    MailerKind, QuarantineConfig, Refusal, Registration, RegistrationConfig, RegistrationListQuery,
+    RegistrationStatus,
+};
+pub use repository::RegistrationRepository;
+
+use repository::NewRegistration;
+
+use std::sync::Arc;
+
+use anyhow::{Result, bail};
+use chrono::{Duration, Utc};
+use sha2::{Digest, Sha256};
+use tracing::{info, warn};
+
+use crate::db;
+use crate::outbox::address_of;
+
+/// Generate a verification token.
+fn generate_token() -> String {
+    let bytes: [u8; 32] = rand::random();
+    hex::encode(bytes)
+}
+
+/// Hash a token for storage/lookup.
+fn hash_token(token: &str) -> String {
+    hex::encode(Sha256::digest(token.trim().as_bytes()))
+}
+
+/// Open registration service.
+pub struct RegistrationService {
+    repo: RegistrationRepository,
+    config: RegistrationConfig,
+    mailer: Arc<dyn Mailer>,
+}
+
+impl RegistrationService {
+    pub fn new(repo: RegistrationRepository, config: RegistrationConfig) -> Result<Self> {
+        if config.verification_ttl_hours <= 0 {
+            bail!("registration.verification_ttl_hours must be positive");
+        }
+        if config.quarantine.max_concurrent_sessions < 0 {
+            bail!("registration.quarantine.max_concurrent_sessions must not be negative");
+        }
+        if config.require_email_verification {
+            if config.public_url.trim().is_empty() {
+                bail!("registration.public_url must be set to send verification links");
+            }
+            if config.mailer == MailerKind::Sendmail && address_of(&config.email.from).is_none() {
+                bail!("registration.email.from must be an email address");
+            }
+        }
+        let mailer: Arc<dyn Mailer> = match config.mailer {
+            MailerKind::Sendmail => Arc::new(SendmailMailer::new(config.email.clone())),
+            MailerKind::Log => Arc::new(LogMailer),
+        };
+        Ok(Self {
+            repo,
+            config,
+            mailer,
+        })
+    }
+
+    /// Deliver emails with `mailer` instead of the configured one.
+    pub fn with_mailer(mut self, mailer: Arc<dyn Mailer>) -> Self {
+        self.mailer = mailer;
+        self
+    }
+
+    pub fn config(&self) -> &RegistrationConfig {
+        &self.config
+    }
+
+    /// Whether registrations without an invite code are accepted.
+    pub fn is_open(&self) -> bool {
+        self.config.open
+    }
+
+    /// Why an open registration for `email` from `client_ip` is turned away,
+    /// if it is.
+    pub async fn admit(&self, email: &str, client_ip: Option<&str>) -> Result<Option<Refusal>> {
+        if !self.config.allowed_email_domains.is_empty() {
+            let domain = address_of(email)
+                .and_then(|addr| addr.rsplit_once('@'))
+                .map(|(_, domain)| domain.to_ascii_lowercase());
+            let allowed = domain.is_some_and(|domain| {
+                self.config
+                    .allowed_email_domains
+                    .iter()
+                    .any(|allowed| allowed.eq_ignore_ascii_case(&domain))
+            });
+            if !allowed {
+                return Ok(Some(Refusal::EmailDomain));
+            }
+        }
+
+        let now = Utc::now();
+        if self.config.max_per_ip_per_hour > 0
+            && let Some(client_ip) = client_ip
+        {
+            let since = db::timestamp(now - Duration::hours(1));
+            if self.repo.count_since(&since, Some(client_ip)).await?
+                >= self.config.max_per_ip_per_hour
+            {
+                return Ok(Some(Refusal::IpLimit));
+            }
+        }
+        if self.config.max_per_day > 0 {
+            let since = db::timestamp(now - Duration::days(1));
+            if self.repo.count_since(&since, None).await? >= self.config.max_per_day {
+                return Ok(Some(Refusal::DailyLimit));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Record the open registration of a user and send the verification
+    /// email. A failed email is logged; the user can request another.
+    pub async fn record(
+        &self,
+        user_id: &str,
+        email: &str,
+        client_ip: Option<&str>,
+    ) -> Result<Registration> {
+        let status = if self.config.require_approval {
+            RegistrationStatus::PendingApproval
+        } else {
+            RegistrationStatus::Active
+        };
+        let verify = self.config.require_email_verification;
+        let token = verify.then(generate_token);
+        let token_hash = token.as_deref().map(hash_token);
+        let expires_at = verify.then(|| self.expiry());
+        self.repo
+            .insert(&NewRegistration {
+                user_id,
+                email,
+                client_ip,
+                status,
+                quarantined: verify,
+                verification_token_hash: token_hash.as_deref(),
+                verification_expires_at: expires_at.as_deref(),
+            })
+            .await?;
+        info!(user_id = %user_id, status = status.as_str(), "Recorded open registration");
+
+        if let Some(token) = token
+            && let Err(e) = self.send_verification(email, &token).await
+        {
+            warn!(user_id = %user_id, "Failed to send verification email: {e:#}");
+        }
+        self.get(user_id).await
+    }
+
+    /// Send a new verification link to a quarantined user.
+    pub async fn resend_verification(&self, user_id: &str) -> Result<()> {
+        let registration = self.get(user_id).await?;
+        let token = generate_token();
+        if !self
+            .repo
+            .set_verification_token(user_id, &hash_token(&token), &self.expiry())
+            .await?
+        {
+            bail!("cannot resend the verification link: the email address is already verified");
+        }
+        self.send_verification(&registration.email, &token).await
+    }
+
+    /// Lift the quarantine of the user the token was sent to.
+    pub async fn verify(&self, token: &str) -> Result<Registration> {
+        let Some(user_id) = self.repo.verify(&hash_token(token)).await? else {
+            bail!("invalid or expired verification link");
+        };
+        info!(user_id = %user_id, "Verified registration email address");
+        self.get(&user_id).await
+    }
+
+    pub async fn get(&self, user_id: &str) -> Result<Registration> {
+        match self.repo.get(user_id).await? {
+            Some(registration) => Ok(registration),
+            None => bail!("registration not found"),
+        }
+    }
+
+    /// Registrations with `status`, oldest first.
+    pub async fn list(&self, status: RegistrationStatus) -> Result<Vec<Registration>> {
+        self.repo.list(status).await
+    }
+
+    /// Approve a registration waiting in the queue or rejected before. The
+    /// caller enables the account.
+    pub async fn approve(&self, user_id: &str, reviewer: &str) -> Result<Registration> {
+        let registration = self.get(user_id).await?;
+        if registration.status == RegistrationStatus::Active {
+            bail!("cannot approve a registration that is already active");
+        }
+        self.repo
+            .set_status(user_id, RegistrationStatus::Active, reviewer)
+            .await?;
+        info!(user_id = %user_id, reviewer = %reviewer, "Approved registration");
+        self.get(user_id).await
+    }
+
+    /// Reject a registration waiting in the queue.
+    pub async fn reject(&self, user_id: &str, reviewer: &str) -> Result<Registration> {
+        let registration = self.get(user_id).await?;
+        if registration.status != RegistrationStatus::PendingApproval {
+            bail!(
+                "cannot reject a registration that is {}",
+                registration.status.as_str()
+            );
+        }
+        self.repo
+            .set_status(user_id, RegistrationStatus::Rejected, reviewer)
+            .await?;
+        info!(user_id = %user_id, reviewer = %reviewer, "Rejected registration");
+        self.get(user_id).await
+    }
+
+    /// Most sessions the user may run at once while quarantined, or `None`
+    /// if the user is not quarantined.
+    pub async fn quarantine_session_cap(&self, user_id: &str) -> Result<Option<i64>> {
+        if !self.config.require_email_verification || !self.repo.is_quarantined(user_id).await? {
+            return Ok(None);
+        }
+        Ok(Some(self.config.quarantine.max_concurrent_sessions))
+    }
+
+    fn expiry(&self) -> String {
+        db::timestamp(Utc::now() + Duration::hours(self.config.verification_ttl_hours))
+    }
+
+    fn verification_url(&self, token: &str) -> String {
+        format!(
+            "{}/api/auth/verify-email?token={token}",
+            self.config.public_url.trim_end_matches('/')
+        )
+    }
+
+    async fn send_verification(&self, email: &str, token: &str) -> Result<()> {
+        let body = format!(
+            "Welcome to Oqto!\n\n\
+             Confirm your email address by opening this link:\n\n\
+             {}\n\n\
+             The link expires in {} hours. If you did not create an account, \
+             you can ignore this email.\n",
+            self.verification_url(token),
+            self.config.verification_ttl_hours
+        );
+        self.mailer
+            .send(email, "Confirm your email address", &body)
+            .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use async_trait::async_trait;
+
+    use super::*;
+    use crate::db::Database;
+
+    #[derive(Default)]
+    struct RecordingMailer {
+        sent: Mutex<Vec<(String, String)>>,
+    }
+
+    impl RecordingMailer {
+        /// Token of the last verification link sent.
+        fn last_token(&self) -> String {
+            let sent = self.sent.lock().unwrap();
+            let (_, body) = sent.last().expect("no email sent");
+            let (_, rest) = body.split_once("token=").unwrap();
+            rest.split_whitespace().next().unwrap().to_string()
+        }
+    }
+
+    #[async_trait]
+    impl Mailer for RecordingMailer {
+        async fn send(&self, to: &str, _subject: &str, body: &str) -> Result<()> {
+            self.sent
+                .lock()
+                .unwrap()
+                .push((to.to_string(), body.to_string()));
+            Ok(())
+        }
+    }
+
+    async fn service(
+        config: RegistrationConfig,
+    ) -> (Database, RegistrationService, Arc<RecordingMailer>) {
+        let db = Database::in_memory().await.unwrap();
+        for user in ["alice", "bob", "carol"] {
+            sqlx::query(
+                "INSERT INTO users (id, username, email, display_name) VALUES (?, ?, ?, ?)",
+            )
+            .bind(user)
+            .bind(user)
+            .bind(format!("{user}@example.com"))
+            .bind(user)
+            .execute(db.pool())
+            .await
+            .unwrap();
+        }
+        let mailer = Arc::new(RecordingMailer::default());
+        let service = RegistrationService::new(
+            RegistrationRepository::new(db.shared().clone()),
+            RegistrationConfig {
+                open: true,
+                public_url: "https://oqto.example.com/".to_string(),
+                mailer: MailerKind::Log,
+                ..config
+            },
+        )
+        .unwrap()
+        .with_mailer(mailer.clone());
+        (db, service, mailer)
+    }
+
+    #[tokio::test]
+    async fn test_admission_limits() {
+        let (_db, service, _) = service(RegistrationConfig {
+            allowed_email_domains: vec!["Example.com".to_string()],
+            max_per_ip_per_hour: 1,
+            max_per_day: 2,
+            ..Default::default()
+        })
+        .await;
+
+        assert_eq!(
+            service
+                .admit("eve@evil.test", Some("10.0.0.1"))
+                .await
+                .unwrap(),
+            Some(Refusal::EmailDomain)
+        );
+        assert_eq!(
+            service
+                .admit("alice@example.com", Some("10.0.0.1"))
+                .await
+                .unwrap(),
+            None
+        );
+        service
+            .record("alice", "alice@example.com", Some("10.0.0.1"))
+            .await
+            .unwrap();
+        assert_eq!(
+            service
+                .admit("bob@example.com", Some("10.0.0.1"))
+                .await
+                .unwrap(),
+            Some(Refusal::IpLimit)
+        );
+        service
+            .record("bob", "bob@example.com", Some("10.0.0.2"))
+            .await
+            .unwrap();
+        assert_eq!(
+            service
+                .admit("carol@example.com", Some("10.0.0.3"))
+                .await
+                .unwrap(),
+            Some(Refusal::DailyLimit)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_verification_lifts_quarantine() {
+        let (_db, service, mailer) = service(RegistrationConfig::default()).await;
+
+        let registration = service
+            .record("alice", "alice@example.com", None)
+            .await
+            .unwrap();
+        assert!(registration.quarantined);
+        assert_eq!(registration.status, RegistrationStatus::Active);
+        assert_eq!(
+            service.quarantine_session_cap("alice").await.unwrap(),
+            Some(1)
+        );
+        let first = mailer.last_token();
+        assert!(
+            mailer.sent.lock().unwrap()[0]
+                .1
+                .contains("https://oqto.example.com/api/auth/verify-email?token=")
+        );
+
+        // A new link replaces the old one.
+        service.resend_verification("alice").await.unwrap();
+        assert!(service.verify(&first).await.is_err());
+        let registration = service.verify(&mailer.last_token()).await.unwrap();
+        assert!(!registration.quarantined);
+        assert!(registration.verified_at.is_some());
+        assert_eq!(service.quarantine_session_cap("alice").await.unwrap(), None);
+        assert!(service.resend_verification("alice").await.is_err());
+        // Users who registered with an invite code have no registration.
+        assert_eq!(service.quarantine_session_cap("bob").await.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn test_approval_queue() {
+        let (_db, service, _) = service(RegistrationConfig {
+            require_approval: true,
+            require_email_verification: false,
+            ..Default::default()
+        })
+        .await;
+        service
+            .record("alice", "alice@example.com", None)
+            .await
+            .unwrap();
+        service
+            .record("bob", "bob@example.com", None)
+            .await
+            .unwrap();
+
+        let pending = service
+            .list(RegistrationStatus::PendingApproval)
+            .await
+            .unwrap();
+        assert_eq!(pending.len(), 2);
+        assert_eq!(pending[0].username, "alice");
+        assert!(!pending[0].quarantined);
+
+        let approved = service.approve("alice", "admin").await.unwrap();
+        assert_eq!(approved.status, RegistrationStatus::Active);
+        assert_eq!(approved.reviewed_by.as_deref(), Some("admin"));
+        assert!(service.approve("alice", "admin").await.is_err());
+
+        service.reject("bob", "admin").await.unwrap();
+        assert!(service.reject("bob", "admin").await.is_err());
+        assert_eq!(
+            service
+                .list(RegistrationStatus::PendingApproval)
+                .await
+                .unwrap()
+                .len(),
+            0
+        );
+        assert!(service.get("carol").await.is_err());
+    }
+}
@@ -0,0 +1,159 @@
+use serde::{Deserialize, Serialize};
+
+use crate::outbox::EmailSenderConfig;
+
+/// Open registration configuration.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RegistrationConfig {
+    /// Accept registrations without an invite code.
+    pub open: bool,
+    /// Quarantine new users until they follow the link in the verification
+    /// email.
+    pub require_email_verification: bool,
+    /// Keep new accounts disabled until an admin approves them.
+    pub require_approval: bool,
+    /// Email domains accepted; empty accepts any.
+    pub allowed_email_domains: Vec<String>,
+    /// Open registrations accepted per client IP per hour (0 = unlimited).
+    pub max_per_ip_per_hour: i64,
+    /// Open registrations accepted per day across all clients
+    /// (0 = unlimited).
+    pub max_per_day: i64,
+    /// How long verification links stay valid.
+    pub verification_ttl_hours: i64,
+    /// URL users reach oqto at, e.g. `https://oqto.example.com`.
+    /// Verification links point here.
+    pub public_url: String,
+    /// How verification emails are delivered.
+    pub mailer: MailerKind,
+    /// Sender of verification emails (`from` and `sendmail_path`).
+    pub email: EmailSenderConfig,
+    pub quarantine: QuarantineConfig,
+}
+
+impl Default for RegistrationConfig {
+    fn default() -> Self {
+        Self {
+            open: false,
+            require_email_verification: true,
+            require_approval: false,
+            allowed_email_domains: Vec::new(),
+            max_per_ip_per_hour: 5,
+            max_per_day: 100,
+            verification_ttl_hours: 48,
+            public_url: String::new(),
+            mailer: MailerKind::Sendmail,
+            email: EmailSenderConfig::default(),
+            quarantine: QuarantineConfig::default(),
+        }
+    }
+}
+
+/// Delivery of verification emails.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MailerKind {
+    /// The host's sendmail-compatible MTA.
+    Sendmail,
+    /// Only log the verification link (development).
+    Log,
+}
+
+/// Quota of users whose email address is not verified yet.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct QuarantineConfig {
+    /// Sessions a quarantined user may run at once; 0 lets them start none.
+    pub max_concurrent_sessions: i64,
+}
+
+impl Default for QuarantineConfig {
+    fn default() -> Self {
+        Self {
+            max_concurrent_sessions: 1,
+        }
+    }
+}
+
+/// Where an open registration stands with the admins.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RegistrationStatus {
+    /// Waiting in the approval queue; the account is disabled.
+    PendingApproval,
+    /// The account is enabled.
+    Active,
+    /// Turned down by an admin; the account stays disabled.
+    Rejected,
+}
+
+impl RegistrationStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            RegistrationStatus::PendingApproval => "pending_approval",
+            RegistrationStatus::Active => "active",
+            RegistrationStatus::Rejected => "rejected",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "pending_approval" => Some(RegistrationStatus::PendingApproval),
+            "active" => Some(RegistrationStatus::Active),
+            "rejected" => Some(RegistrationStatus::Rejected),
+            _ => None,
+        }
+    }
+}
+
+/// A user who registered without an invite code.
+#[derive(Debug, Clone, Serialize)]
+pub struct Registration {
+    pub user_id: String,
+    pub username: String,
+    pub email: String,
+    pub client_ip: Option<String>,
+    pub status: RegistrationStatus,
+    /// Whether the user is held to the quarantine quota.
+    pub quarantined: bool,
+    /// When the current verification link expires.
+    pub verification_expires_at: Option<String>,
+    pub verified_at: Option<String>,
+    pub reviewed_by: Option<String>,
+    pub reviewed_at: Option<String>,
+    pub created_at: String,
+}
+
+/// Query parameters for the approval queue.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct RegistrationListQuery {
+    /// Only registrations with this status (default: pending approval).
+    pub status: Option<RegistrationStatus>,
+}
+
+/// Why an open registration was turned away.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Refusal {
+    /// The email domain is not in `allowed_email_domains`.
+    EmailDomain,
+    /// The client IP reached `max_per_ip_per_hour`.
+    IpLimit,
+    /// `max_per_day` was reached.
+    DailyLimit,
+}
+
+impl Refusal {
+    pub fn message(&self) -> &'static str {
+        match self {
+            Refusal::EmailDomain => "Registration is not open to this email domain",
+            Refusal::IpLimit => "Too many registrations from this address, try again later",
+            Refusal::DailyLimit => "Registration is closed for today, try again tomorrow",
+        }
+    }
+
+    /// Whether the client may succeed by retrying later.
+    pub fn is_rate_limit(&self) -> bool {
+        !matches!(self, Refusal::EmailDomain)
+    }
+}
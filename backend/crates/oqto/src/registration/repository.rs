@@ -0,0 +1,230 @@
+use anyhow::{Context, Result};
+use sqlx::FromRow;
+
+use crate::db::{self, DbPool, on_pool};
+
+use super::{Registration, RegistrationStatus};
+
+const REGISTRATION_SELECT: &str = r#"SELECT r.user_id, u.username, r.email, r.client_ip, r.status,
+       r.quarantined, r.verification_expires_at, r.verified_at, r.reviewed_by,
+       r.reviewed_at, r.created_at
+FROM registrations r
+JOIN users u ON u.id = r.user_id"#;
+
+#[derive(Debug, Clone, FromRow)]
+struct RegistrationRow {
+    user_id: String,
+    username: String,
+    email: String,
+    client_ip: Option<String>,
+    status: String,
+    quarantined: bool,
+    verification_expires_at: Option<String>,
+    verified_at: Option<String>,
+    reviewed_by: Option<String>,
+    reviewed_at: Option<String>,
+    created_at: String,
+}
+
+impl From<RegistrationRow> for Registration {
+    fn from(row: RegistrationRow) -> Self {
+        Self {
+            user_id: row.user_id,
+            username: row.username,
+            email: row.email,
+            client_ip: row.client_ip,
+            // The column is constrained to the known values.
+            status: RegistrationStatus::parse(&row.status)
+                .unwrap_or(RegistrationStatus::PendingApproval),
+            quarantined: row.quarantined,
+            verification_expires_at: row.verification_expires_at,
+            verified_at: row.verified_at,
+            reviewed_by: row.reviewed_by,
+            reviewed_at: row.reviewed_at,
+            created_at: row.created_at,
+        }
+    }
+}
+
+/// A registration to record.
+#[derive(Debug, Clone)]
+pub struct NewRegistration<'a> {
+    pub user_id: &'a str,
+    pub email: &'a str,
+    pub client_ip: Option<&'a str>,
+    pub status: RegistrationStatus,
+    pub quarantined: bool,
+    pub verification_token_hash: Option<&'a str>,
+    pub verification_expires_at: Option<&'a str>,
+}
+
+#[derive(Debug, Clone)]
+pub struct RegistrationRepository {
+    pool: DbPool,
+}
+
+impl RegistrationRepository {
+    pub fn new(pool: DbPool) -> Self {
+        Self { pool }
+    }
+
+    pub async fn insert(&self, registration: &NewRegistration<'_>) -> Result<()> {
+        on_pool!(&self.pool, |pool| sqlx::query(
+            r#"INSERT INTO registrations
+                   (user_id, email, client_ip, status, quarantined,
+                    verification_token_hash, verification_expires_at, created_at)
+               VALUES ($1, $2, $3, $4, $5, $6, $7, $8)"#
+        )
+        .bind(registration.user_id)
+        .bind(registration.email)
+        .bind(registration.client_ip)
+        .bind(registration.status.as_str())
+        .bind(registration.quarantined)
+        .bind(registration.verification_token_hash)
+        .bind(registration.verification_expires_at)
+        .bind(db::now())
+        .execute(pool)
+        .await)
+        .context("insert registration")?;
+        Ok(())
+    }
+
+    pub async fn get(&self, user_id: &str) -> Result<Option<Registration>> {
+        let sql = format!("{REGISTRATION_SELECT} WHERE r.user_id = $1");
+        let row = on_pool!(&self.pool, |pool| sqlx::query_as::<_, RegistrationRow>(
+            &sql
+        )
+        .bind(user_id)
+        .fetch_optional(pool)
+        .await)
+        .context("get registration")?;
+        Ok(row.map(Into::into))
+    }
+
+    /// Registrations with `status`, oldest first.
+    pub async fn list(&self, status: RegistrationStatus) -> Result<Vec<Registration>> {
+        let sql =
+            format!("{REGISTRATION_SELECT} WHERE r.status = $1 ORDER BY r.created_at, r.user_id");
+        let rows = on_pool!(&self.pool, |pool| sqlx::query_as::<_, RegistrationRow>(
+            &sql
+        )
+        .bind(status.as_str())
+        .fetch_all(pool)
+        .await)
+        .context("list registrations")?;
+        Ok(rows.into_iter().map(Into::into).collect())
+    }
+
+    /// Registrations recorded at or after `since`, from `client_ip` if given.
+    pub async fn count_since(&self, since: &str, client_ip: Option<&str>) -> Result<i64> {
+        match client_ip {
+            Some(client_ip) => on_pool!(&self.pool, |pool| sqlx::query_scalar(
+                "SELECT COUNT(*) FROM registrations WHERE created_at >= $1 AND client_ip = $2"
+            )
+            .bind(since)
+            .bind(client_ip)
+            .fetch_one(pool)
+            .await),
+            None => on_pool!(&self.pool, |pool| sqlx::query_scalar(
+                "SELECT COUNT(*) FROM registrations WHERE created_at >= $1"
+            )
+            .bind(since)
+            .fetch_one(pool)
+            .await),
+        }
+        .context("count registrations")
+    }
+
+    /// Whether the user is held to the quarantine quota.
+    pub async fn is_quarantined(&self, user_id: &str) -> Result<bool> {
+        let quarantined: Option<bool> = on_pool!(&self.pool, |pool| sqlx::query_scalar(
+            "SELECT quarantined FROM registrations WHERE user_id = $1"
+        )
+        .bind(user_id)
+        .fetch_optional(pool)
+        .await)
+        .context("query registration quarantine")?;
+        Ok(quarantined.unwrap_or(false))
+    }
+
+    /// Replace the verification token of a quarantined user. Returns whether
+    /// the user is quarantined.
+    pub async fn set_verification_token(
+        &self,
+        user_id: &str,
+        token_hash: &str,
+        expires_at: &str,
+    ) -> Result<bool> {
+        let updated = on_pool!(&self.pool, |pool| sqlx::query(
+            r#"UPDATE registrations
+               SET verification_token_hash = $1, verification_expires_at = $2
+               WHERE user_id = $3 AND quarantined = $4"#
+        )
+        .bind(token_hash)
+        .bind(expires_at)
+        .bind(user_id)
+        .bind(true)
+        .execute(pool)
+        .await
+        .map(|r| r.rows_affected()))
+        .context("set verification token")?;
+        Ok(updated > 0)
+    }
+
+    /// Lift the quarantine of the user whose unexpired token hashes to
+    /// `token_hash`. Returns the user's id.
+    pub async fn verify(&self, token_hash: &str) -> Result<Option<String>> {
+        let now = db::now();
+        let user_id: Option<String> = on_pool!(&self.pool, |pool| sqlx::query_scalar(
+            r#"SELECT user_id FROM registrations
+               WHERE verification_token_hash = $1 AND verification_expires_at > $2"#
+        )
+        .bind(token_hash)
+        .bind(&now)
+        .fetch_optional(pool)
+        .await)
+        .context("look up verification token")?;
+        let Some(user_id) = user_id else {
+            return Ok(None);
+        };
+
+        let updated = on_pool!(&self.pool, |pool| sqlx::query(
+            r#"UPDATE registrations
+               SET quarantined = $1, verified_at = $2,
+                   verification_token_hash = NULL, verification_expires_at = NULL
+               WHERE user_id = $3 AND verification_token_hash = $4"#
+        )
+        .bind(false)
+        .bind(&now)
+        .bind(&user_id)
+        .bind(token_hash)
+        .execute(pool)
+        .await
+        .map(|r| r.rows_affected()))
+        .context("verify registration")?;
+        Ok((updated > 0).then_some(user_id))
+    }
+
+    /// Record an admin's decision on a registration.
+    pub async fn set_status(
+        &self,
+        user_id: &str,
+        status: RegistrationStatus,
+        reviewed_by: &str,
+    ) -> Result<bool> {
+        let updated = on_pool!(&self.pool, |pool| sqlx::query(
+            r#"UPDATE registrations
+               SET status = $1, reviewed_by = $2, reviewed_at = $3
+               WHERE user_id = $4"#
+        )
+        .bind(status.as_str())
+        .bind(reviewed_by)
+        .bind(db::now())
+        .bind(user_id)
+        .execute(pool)
+        .await
+        .map(|r| r.rows_affected()))
+        .context("set registration status")?;
+        Ok(updated > 0)
+    }
+}
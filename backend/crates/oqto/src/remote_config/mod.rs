@@ -0,0 +1,431 @@
+//! Signed remote configuration for fleet deployments.
+//!
+//! With `--config https://...` or `[config] remote_url`, the server fetches a
+//! TOML config bundle and its minisign signature (`<url>.minisig`) over HTTPS
+//! at startup and every `refresh_interval_secs`. A bundle is only accepted if
+//! its signature verifies against `[config] public_key`. The last accepted
+//! bundle is cached next to config.toml (`remote.toml`, `remote.toml.minisig`)
+//! and re-verified on every start, so boxes can start offline. It is layered
+//! over the local config file; environment variables still win.
+//!
+//! Bundles are ordered by the `timestamp:` minisign writes into the signed
+//! trusted comment. A bundle signed before the cached one is refused, so an
+//! old, validly signed bundle cannot be replayed to roll a setting back.
+//!
+//! When a refresh brings a new bundle, it goes through the
+//! [`ConfigReloader`]: the settings service, session limits, EAVS budgets and
+//! the other live settings apply right away. Only when other sections change
+//! does the server shut down gracefully with [`RESTART_EXIT_CODE`] and let the
+//! service manager restart it (`restart_on_change = false` only logs the
+//! pending change).
+
+use std::collections::BTreeSet;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::{Context, Result, bail};
+use minisign_verify::{PublicKey, Signature};
+use serde::{Deserialize, Serialize};
+use tokio::sync::Notify;
+use tracing::{info, warn};
+
+use crate::config_reload::ConfigReloader;
+
+/// Exit code after a graceful shutdown that applies a new bundle. Non-zero so
+/// `Restart=on-failure` units are restarted.
+pub const RESTART_EXIT_CODE: i32 = 75;
+
+/// Cached bundle file name, next to config.toml.
+pub const CACHE_FILE: &str = "remote.toml";
+
+/// Largest accepted bundle or signature.
+const MAX_BUNDLE_BYTES: usize = 1024 * 1024;
+/// Timeout for one bundle or signature download.
+const FETCH_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// `[config]` section: where the signed bundle comes from.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RemoteConfigSettings {
+    /// HTTPS URL of the config bundle. Empty disables remote config.
+    pub remote_url: String,
+    /// Minisign public key bundles must be signed with: the base64 key line
+    /// of the `.pub` file, or the whole file.
+    pub public_key: String,
+    /// Seconds between refreshes. 0 fetches only at startup.
+    pub refresh_interval_secs: u64,
+    /// Restart the server when a new bundle changes startup-only settings.
+    pub restart_on_change: bool,
+    /// Watch config.toml and apply changes to live settings (log level,
+    /// session limits, EAVS budgets, voice) without a restart.
+    pub watch: bool,
+}
+
+impl Default for RemoteConfigSettings {
+    fn default() -> Self {
+        Self {
+            remote_url: String::new(),
+            public_key: String::new(),
+            refresh_interval_secs: 300,
+            restart_on_change: true,
+            watch: true,
+        }
+    }
+}
+
+/// Whether a `--config` argument names a remote bundle rather than a file.
+pub fn is_remote_url(value: &str) -> bool {
+    value.starts_with("https://") || value.starts_with("http://")
+}
+
+/// Parse a minisign public key from its base64 line or a whole `.pub` file.
+fn parse_public_key(text: &str) -> Result<PublicKey> {
+    let text = text.trim();
+    let key = if text.contains('\n') {
+        PublicKey::decode(text)
+    } else {
+        PublicKey::from_base64(text)
+    };
+    key.map_err(|e| anyhow::anyhow!("invalid minisign public key: {e}"))
+}
+
+/// Signing time from a trusted comment (`timestamp:<unix>\tfile:...`).
+fn signed_at(trusted_comment: &str) -> Option<u64> {
+    trusted_comment
+        .split('\t')
+        .find_map(|field| field.strip_prefix("timestamp:")?.parse().ok())
+}
+
+/// Verify a bundle against its minisign signature and check it is TOML.
+/// Returns when it was signed.
+pub fn verify_bundle(public_key: &str, content: &str, signature: &str) -> Result<u64> {
+    let public_key = parse_public_key(public_key)?;
+    let signature = Signature::decode(signature)
+        .map_err(|e| anyhow::anyhow!("invalid minisign signature: {e}"))?;
+    public_key
+        .verify(content.as_bytes(), &signature, false)
+        .map_err(|e| anyhow::anyhow!("signature verification failed: {e}"))?;
+    let signed_at = signed_at(signature.trusted_comment())
+        .context("signature has no timestamp in its trusted comment")?;
+    content
+        .parse::<toml::Value>()
+        .context("remote config bundle is not valid TOML")?;
+    Ok(signed_at)
+}
+
+/// Refuse a bundle signed before the cached one.
+fn ensure_not_older(signed_at: u64, cached_at: Option<u64>) -> Result<()> {
+    match cached_at {
+        Some(cached_at) if signed_at < cached_at => bail!(
+            "remote config bundle signed at {signed_at} is older than the cached one \
+             (signed at {cached_at}); refusing to roll back"
+        ),
+        _ => Ok(()),
+    }
+}
+
+/// Top-level sections that differ between two bundles.
+pub fn changed_sections(old: &str, new: &str) -> Vec<String> {
+    let table = |text: &str| match text.parse::<toml::Value>() {
+        Ok(toml::Value::Table(table)) => table,
+        _ => toml::map::Map::new(),
+    };
+    let (old, new) = (table(old), table(new));
+    let keys: BTreeSet<&String> = old.keys().chain(new.keys()).collect();
+    keys.into_iter()
+        .filter(|key| old.get(*key) != new.get(*key))
+        .cloned()
+        .collect()
+}
+
+/// A signed bundle and where it is cached.
+#[derive(Debug, Clone)]
+pub struct RemoteConfigSource {
+    url: String,
+    public_key: String,
+    cache_dir: PathBuf,
+}
+
+impl RemoteConfigSource {
+    /// Validate the URL and public key. `cache_dir` is the config directory.
+    pub fn new(url: &str, public_key: &str, cache_dir: &Path) -> Result<Self> {
+        if !url.starts_with("https://") {
+            bail!("remote config must be fetched over HTTPS: {url}");
+        }
+        if public_key.trim().is_empty() {
+            bail!("remote config requires [config] public_key or --config-public-key");
+        }
+        parse_public_key(public_key)?;
+        Ok(Self {
+            url: url.to_string(),
+            public_key: public_key.to_string(),
+            cache_dir: cache_dir.to_path_buf(),
+        })
+    }
+
+    pub fn cache_path(&self) -> PathBuf {
+        self.cache_dir.join(CACHE_FILE)
+    }
+
+    fn signature_cache_path(&self) -> PathBuf {
+        self.cache_dir.join(format!("{CACHE_FILE}.minisig"))
+    }
+
+    /// The cached bundle, if present and still correctly signed.
+    pub fn load_cached(&self) -> Result<Option<String>> {
+        Ok(self.load_cached_signed()?.map(|(content, _)| content))
+    }
+
+    /// The cached bundle and when it was signed.
+    fn load_cached_signed(&self) -> Result<Option<(String, u64)>> {
+        let path = self.cache_path();
+        if !path.exists() {
+            return Ok(None);
+        }
+        let content = std::fs::read_to_string(&path)
+            .with_context(|| format!("reading {}", path.display()))?;
+        let signature = std::fs::read_to_string(self.signature_cache_path())
+            .context("reading cached remote config signature")?;
+        let signed_at = verify_bundle(&self.public_key, &content, &signature)
+            .context("cached remote config bundle")?;
+        Ok(Some((content, signed_at)))
+    }
+
+    /// Fetch and verify the bundle. Returns its content when it differs from
+    /// the cached one, after caching it.
+    pub async fn refresh(&self, client: &reqwest::Client) -> Result<Option<String>> {
+        let content = fetch_text(client, &self.url).await?;
+        let signature = fetch_text(client, &format!("{}.minisig", self.url)).await?;
+        self.accept(content, &signature)
+    }
+
+    /// Verify a fetched bundle and cache it unless it is already cached or
+    /// older than the cached one.
+    fn accept(&self, content: String, signature: &str) -> Result<Option<String>> {
+        let signed_at = verify_bundle(&self.public_key, &content, signature)?;
+
+        // A cache that no longer verifies does not block a valid bundle.
+        let cached = self.load_cached_signed().ok().flatten();
+        if let Some((cached, _)) = &cached
+            && *cached == content
+        {
+            return Ok(None);
+        }
+        ensure_not_older(signed_at, cached.map(|(_, cached_at)| cached_at))?;
+        self.store(&content, signature)?;
+        Ok(Some(content))
+    }
+
+    fn store(&self, content: &str, signature: &str) -> Result<()> {
+        std::fs::create_dir_all(&self.cache_dir)
+            .with_context(|| format!("creating {}", self.cache_dir.display()))?;
+        // Signature first: a crash in between leaves a bundle that fails
+        // verification rather than a stale signature that passes.
+        write_atomic(&self.signature_cache_path(), signature)?;
+        write_atomic(&self.cache_path(), content)
+    }
+}
+
+fn write_atomic(path: &Path, content: &str) -> Result<()> {
+    let tmp = path.with_extension("tmp");
+    std::fs::write(&tmp, content).with_context(|| format!("writing {}", tmp.display()))?;
+    std::fs::rename(&tmp, path).with_context(|| format!("replacing {}", path.display()))
+}
+
+async fn fetch_text(client: &reqwest::Client, url: &str) -> Result<String> {
+    let response = client
+        .get(url)
+        .timeout(FETCH_TIMEOUT)
+        .send()
+        .await
+        .with_context(|| format!("fetching {url}"))?;
+    if !response.status().is_success() {
+        bail!("fetching {url}: HTTP {}", response.status());
+    }
+    if response
+        .content_length()
+        .is_some_and(|len| len > MAX_BUNDLE_BYTES as u64)
+    {
+        bail!("{url} exceeds {MAX_BUNDLE_BYTES} bytes");
+    }
+    let bytes = response
+        .bytes()
+        .await
+        .with_context(|| format!("reading {url}"))?;
+    if bytes.len() > MAX_BUNDLE_BYTES {
+        bail!("{url} exceeds {MAX_BUNDLE_BYTES} bytes");
+    }
+    String::from_utf8(bytes.to_vec()).with_context(|| format!("{url} is not UTF-8"))
+}
+
+/// Periodically refreshes the bundle while the server runs.
+pub struct RemoteConfigWatcher {
+    source: RemoteConfigSource,
+    settings: RemoteConfigSettings,
+    client: reqwest::Client,
+    /// Bundle the server is running with.
+    current: String,
+    reloader: Arc<ConfigReloader>,
+    restart: Arc<Notify>,
+}
+
+impl RemoteConfigWatcher {
+    pub fn new(
+        source: RemoteConfigSource,
+        settings: RemoteConfigSettings,
+        reloader: Arc<ConfigReloader>,
+        restart: Arc<Notify>,
+    ) -> Self {
+        let current = source.load_cached().ok().flatten().unwrap_or_default();
+        Self {
+            source,
+            settings,
+            client: reqwest::Client::new(),
+            current,
+            reloader,
+            restart,
+        }
+    }
+
+    /// Start refreshing. Does nothing when the refresh interval is 0.
+    pub fn spawn(mut self) {
+        if self.settings.refresh_interval_secs == 0 {
+            return;
+        }
+        let period = Duration::from_secs(self.settings.refresh_interval_secs);
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(period);
+            // The first tick completes immediately; startup already fetched.
+            interval.tick().await;
+            loop {
+                interval.tick().await;
+                match self.source.refresh(&self.client).await {
+                    Ok(Some(content)) => {
+                        if self.apply(content).await {
+                            return;
+                        }
+                    }
+                    Ok(None) => {}
+                    Err(e) => warn!("Remote config refresh failed: {e:#}"),
+                }
+            }
+        });
+    }
+
+    /// Apply a new bundle. Returns true when a restart was requested.
+    async fn apply(&mut self, content: String) -> bool {
+        let changed = changed_sections(&self.current, &content);
+        self.current = content;
+        if changed.is_empty() {
+            return false;
+        }
+        info!(sections = ?changed, "Remote config bundle changed");
+
+        // The reloader layers the cached bundle, which is now the new one,
+        // applies the live settings and reports what needs a restart.
+        let report = match self.reloader.reload().await {
+            Ok(report) => report,
+            Err(e) => {
+                warn!("Failed to apply remote config, keeping the running config: {e:#}");
+                return false;
+            }
+        };
+        if report.restart_required.is_empty() || !self.settings.restart_on_change {
+            return false;
+        }
+        info!(sections = ?report.restart_required, "Restarting to apply remote config");
+        self.restart.notify_one();
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Test key pair; SIGNATURE is a prehashed signature of BUNDLE.
+    const PUBLIC_KEY: &str = "RWQBI0VniavN7/+DYj3/Wjnt9gccRrsT7oOo+r2AIiTUr0esmstp1B1R";
+    const BUNDLE: &str = "[voice]\nenabled = true\n";
+    const SIGNATURE: &str = "untrusted comment: signature from minisign secret key
+RUQBI0VniavN7wtTSebm7+5YMNQtpKntwVIXRt1bt7eyth/LmSIwojzNXHru6diluYw03QoiwwfgnlSr7LY1WuwmXaVJLT/W/AY=
+trusted comment: timestamp:1760000000\tfile:remote.toml\thashed
+U/qWNa8EBB/qYldHgHRu4qOlMvM2jr6Kn8wrXczqq78hNmXOT1HdAWBDJu2/danU/BnJhMTOA6qNgfKF0HsRCg==
+";
+
+    #[test]
+    fn test_verify_bundle() {
+        assert_eq!(
+            verify_bundle(PUBLIC_KEY, BUNDLE, SIGNATURE).unwrap(),
+            1760000000
+        );
+
+        let err = verify_bundle(PUBLIC_KEY, "[voice]\nenabled = false\n", SIGNATURE).unwrap_err();
+        assert!(format!("{err:#}").contains("signature verification failed"));
+        assert!(verify_bundle("not a key", BUNDLE, SIGNATURE).is_err());
+    }
+
+    #[test]
+    fn test_cached_bundle_is_reverified() {
+        let dir = tempfile::tempdir().unwrap();
+        let source =
+            RemoteConfigSource::new("https://cfg.example/oqto.toml", PUBLIC_KEY, dir.path())
+                .unwrap();
+        assert!(source.load_cached().unwrap().is_none());
+
+        source.store(BUNDLE, SIGNATURE).unwrap();
+        assert_eq!(source.load_cached().unwrap().as_deref(), Some(BUNDLE));
+
+        std::fs::write(source.cache_path(), "[voice]\nenabled = false\n").unwrap();
+        assert!(source.load_cached().is_err());
+    }
+
+    #[test]
+    fn test_older_bundles_are_refused() {
+        assert_eq!(
+            signed_at("timestamp:1760000000\tfile:remote.toml\thashed"),
+            Some(1760000000)
+        );
+        assert_eq!(signed_at("fleet config v3"), None);
+
+        ensure_not_older(1760000000, None).unwrap();
+        ensure_not_older(1760000000, Some(1760000000)).unwrap();
+        ensure_not_older(1760000001, Some(1760000000)).unwrap();
+        let err = ensure_not_older(1759999999, Some(1760000000)).unwrap_err();
+        assert!(err.to_string().contains("refusing to roll back"));
+
+        let dir = tempfile::tempdir().unwrap();
+        let source =
+            RemoteConfigSource::new("https://cfg.example/oqto.toml", PUBLIC_KEY, dir.path())
+                .unwrap();
+        assert_eq!(
+            source
+                .accept(BUNDLE.to_string(), SIGNATURE)
+                .unwrap()
+                .as_deref(),
+            Some(BUNDLE)
+        );
+        assert!(
+            source
+                .accept(BUNDLE.to_string(), SIGNATURE)
+                .unwrap()
+                .is_none()
+        );
+    }
+
+    #[test]
+    fn test_source_requires_https_and_key() {
+        let dir = std::env::temp_dir();
+        assert!(RemoteConfigSource::new("http://cfg.example/oqto.toml", PUBLIC_KEY, &dir).is_err());
+        assert!(RemoteConfigSource::new("https://cfg.example/oqto.toml", "", &dir).is_err());
+        assert!(RemoteConfigSource::new("https://cfg.example/oqto.toml", PUBLIC_KEY, &dir).is_ok());
+    }
+
+    #[test]
+    fn test_changed_sections() {
+        let old = "[voice]\nenabled = false\n\n[sessions]\nmax = 2\n";
+        let new = "[voice]\nenabled = true\n\n[sessions]\nmax = 2\n\n[feedback]\nkeep = 1\n";
+        assert_eq!(changed_sections(old, new), vec!["feedback", "voice"]);
+        assert!(changed_sections(old, old).is_empty());
+    }
+}
@@ -0,0 +1,567 @@
+//! Federation of sessions across several runner hosts.
+//!
+//! By default every session runs on its owner's own runner (the local
+//! socket). With `[[runners]]` configured, new personal sessions are placed
+//! on the least loaded healthy host instead, and every later command for the
+//! session goes to the host it was placed on:
+//!
+//! ```toml
+//! [[runners]]
+//! id = "local"
+//! address = "local"      # each user's own runner
+//! capacity = 4
+//!
+//! [[runners]]
+//! id = "gpu-1"
+//! address = "10.0.0.12:7420"   # oqto-runner --listen 0.0.0.0:7420
+//! token = "..."
+//! capacity = 20
+//! users = ["alice", "bob"]     # empty: everyone
+//! ```
+//!
+//! Remote hosts are pinged every `health_interval_secs`. A host that misses
+//! `failure_threshold` checks in a row is marked down, and sessions that were
+//! idle on it at its last successful check are moved to another host; busy
+//! sessions stay put, since their turn cannot be recovered elsewhere. Moving
+//! a session assumes workspaces and Pi session files live on storage every
+//! host can reach.
+
+use std::collections::HashSet;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use tracing::{debug, info, warn};
+
+use oqto_runner::client::RunnerClient;
+use oqto_runner::protocol::PiSessionState;
+
+use super::placement::RunnerPlacementRepository;
+
+/// Address (and conventional id) of the host standing for each user's own
+/// runner.
+pub const LOCAL_RUNNER: &str = "local";
+
+/// A runner host sessions can be placed on (`[[runners]]`).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RunnerHostConfig {
+    /// Stable id, recorded with each placement.
+    pub id: String,
+    /// `host:port` of a runner started with `--listen`, or `local` for the
+    /// session owner's own runner.
+    pub address: String,
+    /// Token the remote runner was started with.
+    #[serde(default)]
+    pub token: String,
+    /// Maximum number of live sessions on the host. For `local` this applies
+    /// to each user's runner.
+    #[serde(default = "default_capacity")]
+    pub capacity: usize,
+    /// User IDs whose sessions may be placed here (empty: everyone).
+    #[serde(default)]
+    pub users: Vec<String>,
+}
+
+fn default_capacity() -> usize {
+    10
+}
+
+/// Health checking and failover of runner hosts (`[runner_federation]`).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RunnerFederationConfig {
+    /// Seconds between health checks of remote hosts.
+    pub health_interval_secs: u64,
+    /// Seconds a host has to answer a health check or load query.
+    pub health_timeout_secs: u64,
+    /// Consecutive failed checks after which a host is marked down.
+    pub failure_threshold: u32,
+    /// Move idle sessions off hosts that are marked down.
+    pub failover: bool,
+}
+
+impl Default for RunnerFederationConfig {
+    fn default() -> Self {
+        Self {
+            health_interval_secs: 15,
+            health_timeout_secs: 5,
+            failure_threshold: 3,
+            failover: true,
+        }
+    }
+}
+
+/// Health and load of a runner host, as reported to admins.
+#[derive(Debug, Clone, Serialize)]
+pub struct RunnerHostStatus {
+    pub id: String,
+    pub address: String,
+    pub capacity: usize,
+    pub healthy: bool,
+    /// Live sessions at the last successful check (None for `local`).
+    pub sessions: Option<usize>,
+    pub consecutive_failures: u32,
+    pub last_check: Option<DateTime<Utc>>,
+    pub last_error: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+struct HostHealth {
+    healthy: bool,
+    failures: u32,
+    sessions: usize,
+    /// Sessions idle at the last successful check, the ones failover moves.
+    idle: Vec<String>,
+    last_check: Option<DateTime<Utc>>,
+    last_error: Option<String>,
+}
+
+struct RunnerHost {
+    config: RunnerHostConfig,
+    /// None for the local host.
+    client: Option<RunnerClient>,
+    health: Mutex<HostHealth>,
+}
+
+impl RunnerHost {
+    fn accepts(&self, user_id: &str) -> bool {
+        self.config.users.is_empty() || self.config.users.iter().any(|u| u == user_id)
+    }
+
+    fn healthy(&self) -> bool {
+        self.health.lock().expect("runner host health").healthy
+    }
+
+    fn client_or(&self, home: &RunnerClient) -> RunnerClient {
+        self.client.clone().unwrap_or_else(|| home.clone())
+    }
+}
+
+/// Load of a placement candidate.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct HostLoad {
+    sessions: usize,
+    capacity: usize,
+}
+
+/// Index of the candidate with the lowest load relative to its capacity,
+/// skipping full hosts. Ties go to the earlier candidate.
+fn least_loaded(loads: &[HostLoad]) -> Option<usize> {
+    loads
+        .iter()
+        .enumerate()
+        .filter(|(_, load)| load.sessions < load.capacity)
+        .min_by(|(ia, a), (ib, b)| {
+            (a.sessions * b.capacity)
+                .cmp(&(b.sessions * a.capacity))
+                .then(ia.cmp(ib))
+        })
+        .map(|(index, _)| index)
+}
+
+/// Runner hosts sessions are scheduled across.
+pub struct RunnerPool {
+    hosts: Vec<RunnerHost>,
+    placements: RunnerPlacementRepository,
+    config: RunnerFederationConfig,
+}
+
+impl RunnerPool {
+    pub fn new(
+        hosts: Vec<RunnerHostConfig>,
+        config: RunnerFederationConfig,
+        placements: RunnerPlacementRepository,
+    ) -> Result<Self> {
+        let mut ids = HashSet::new();
+        let hosts = hosts
+            .into_iter()
+            .map(|host| {
+                if host.id.trim().is_empty() {
+                    anyhow::bail!("runner host id must not be empty");
+                }
+                if !ids.insert(host.id.clone()) {
+                    anyhow::bail!("duplicate runner host id '{}'", host.id);
+                }
+                if host.capacity == 0 {
+                    anyhow::bail!("runner host '{}' needs a capacity of at least 1", host.id);
+                }
+                let client = if host.address == LOCAL_RUNNER {
+                    None
+                } else if host.token.is_empty() {
+                    anyhow::bail!("remote runner host '{}' needs a token", host.id);
+                } else {
+                    Some(RunnerClient::tcp(&host.address, &host.token))
+                };
+                Ok(RunnerHost {
+                    config: host,
+                    client,
+                    health: Mutex::new(HostHealth {
+                        healthy: true,
+                        failures: 0,
+                        sessions: 0,
+                        idle: Vec::new(),
+                        last_check: None,
+                        last_error: None,
+                    }),
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+        if hosts.is_empty() {
+            anyhow::bail!("no runner hosts configured");
+        }
+        Ok(Self {
+            hosts,
+            placements,
+            config,
+        })
+    }
+
+    fn host(&self, id: &str) -> Option<&RunnerHost> {
+        self.hosts.iter().find(|host| host.config.id == id)
+    }
+
+    /// Whether `client` talks to one of the remote hosts.
+    pub fn owns(&self, client: &RunnerClient) -> bool {
+        self.hosts.iter().any(|host| {
+            host.client
+                .as_ref()
+                .is_some_and(|c| c.socket_path() == client.socket_path())
+        })
+    }
+
+    /// Health and load of every host.
+    pub fn statuses(&self) -> Vec<RunnerHostStatus> {
+        self.hosts
+            .iter()
+            .map(|host| {
+                let health = host.health.lock().expect("runner host health").clone();
+                RunnerHostStatus {
+                    id: host.config.id.clone(),
+                    address: host.config.address.clone(),
+                    capacity: host.config.capacity,
+                    healthy: health.healthy,
+                    sessions: host.client.as_ref().map(|_| health.sessions),
+                    consecutive_failures: health.failures,
+                    last_check: health.last_check,
+                    last_error: health.last_error,
+                }
+            })
+            .collect()
+    }
+
+    /// The runner a personal session runs on. `home` is the owner's own
+    /// runner, used by the `local` host and for sessions that were never
+    /// placed. With `place`, a session without a placement is placed now.
+    pub async fn runner_for_session(
+        &self,
+        user_id: &str,
+        session_id: &str,
+        place: bool,
+        home: &RunnerClient,
+    ) -> Result<RunnerClient> {
+        if let Some(placement) = self.placements.get(session_id).await? {
+            return match self.host(&placement.runner_id) {
+                Some(host) => Ok(host.client_or(home)),
+                None => {
+                    warn!(
+                        session_id,
+                        runner_id = %placement.runner_id,
+                        "Session placed on a runner host that is no longer configured"
+                    );
+                    Ok(home.clone())
+                }
+            };
+        }
+        if !place {
+            return Ok(home.clone());
+        }
+
+        let host = self.place(user_id, home).await?;
+        let placement = self
+            .placements
+            .set_if_absent(session_id, user_id, &host.config.id)
+            .await?;
+        if placement.runner_id != host.config.id {
+            // Another replica placed the session first.
+            return Ok(self
+                .host(&placement.runner_id)
+                .map_or_else(|| home.clone(), |host| host.client_or(home)));
+        }
+        debug!(session_id, runner_id = %host.config.id, "Placed session");
+        Ok(host.client_or(home))
+    }
+
+    /// Pick the least loaded healthy host for a new session of `user_id`,
+    /// asking each candidate for its live sessions.
+    async fn place(&self, user_id: &str, home: &RunnerClient) -> Result<&RunnerHost> {
+        let timeout = Duration::from_secs(self.config.health_timeout_secs.max(1));
+        let mut candidates = Vec::new();
+        let mut loads = Vec::new();
+        for host in self
+            .hosts
+            .iter()
+            .filter(|host| host.accepts(user_id) && host.healthy())
+        {
+            let client = host.client_or(home);
+            match tokio::time::timeout(timeout, client.agent_list_sessions()).await {
+                Ok(Ok(sessions)) => {
+                    candidates.push(host);
+                    loads.push(HostLoad {
+                        sessions: sessions.len(),
+                        capacity: host.config.capacity,
+                    });
+                }
+                Ok(Err(e)) => {
+                    warn!(runner_id = %host.config.id, error = %e, "Runner host load query failed")
+                }
+                Err(_) => {
+                    warn!(runner_id = %host.config.id, "Runner host load query timed out")
+                }
+            }
+        }
+        let index = least_loaded(&loads)
+            .ok_or_else(|| anyhow::anyhow!("no runner host with free capacity"))?;
+        Ok(candidates[index])
+    }
+
+    /// Check remote hosts every `health_interval_secs`.
+    pub fn start_health_task(self: &Arc<Self>) {
+        let pool = Arc::clone(self);
+        tokio::spawn(async move {
+            let mut interval =
+                tokio::time::interval(Duration::from_secs(pool.config.health_interval_secs.max(1)));
+            interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
+            loop {
+                interval.tick().await;
+                pool.check_health().await;
+            }
+        });
+    }
+
+    async fn check_health(&self) {
+        let timeout = Duration::from_secs(self.config.health_timeout_secs.max(1));
+        for host in &self.hosts {
+            let Some(client) = &host.client else {
+                continue;
+            };
+            let result = match tokio::time::timeout(timeout, client.agent_list_sessions()).await {
+                Ok(result) => result,
+                Err(_) => Err(anyhow::anyhow!("health check timed out")),
+            };
+            let now = Utc::now();
+            let failed_over = {
+                let mut health = host.health.lock().expect("runner host health");
+                health.last_check = Some(now);
+                match result {
+                    Ok(sessions) => {
+                        if !health.healthy {
+                            info!(runner_id = %host.config.id, "Runner host is back up");
+                        }
+                        health.healthy = true;
+                        health.failures = 0;
+                        health.last_error = None;
+                        health.sessions = sessions.len();
+                        health.idle = sessions
+                            .into_iter()
+                            .filter(|s| s.state == PiSessionState::Idle)
+                            .map(|s| s.session_id)
+                            .collect();
+                        None
+                    }
+                    Err(e) => {
+                        health.failures += 1;
+                        health.last_error = Some(format!("{e:#}"));
+                        if health.healthy && health.failures >= self.config.failure_threshold {
+                            warn!(
+                                runner_id = %host.config.id,
+                                failures = health.failures,
+                                error = %e,
+                                "Runner host is down"
+                            );
+                            health.healthy = false;
+                            Some(std::mem::take(&mut health.idle))
+                        } else {
+                            None
+                        }
+                    }
+                }
+            };
+            if let Some(idle) = failed_over
+                && self.config.failover
+                && let Err(e) = self.fail_over(host, &idle).await
+            {
+                warn!(runner_id = %host.config.id, error = %e, "Runner host failover failed");
+            }
+        }
+    }
+
+    /// Move the idle sessions placed on `down` to healthy hosts, balancing
+    /// by the load seen at their last check.
+    async fn fail_over(&self, down: &RunnerHost, idle: &[String]) -> Result<()> {
+        let placements = self
+            .placements
+            .on_runner(&down.config.id, idle)
+            .await
+            .context("loading placements to fail over")?;
+        let mut loads: Vec<HostLoad> = self
+            .hosts
+            .iter()
+            .map(|host| HostLoad {
+                sessions: host.health.lock().expect("runner host health").sessions,
+                capacity: host.config.capacity,
+            })
+            .collect();
+        for placement in placements {
+            let eligible: Vec<usize> = self
+                .hosts
+                .iter()
+                .enumerate()
+                .filter(|(_, host)| {
+                    host.config.id != down.config.id
+                        && host.accepts(&placement.user_id)
+                        && host.healthy()
+                })
+                .map(|(index, _)| index)
+                .collect();
+            let candidate_loads: Vec<HostLoad> = eligible.iter().map(|&i| loads[i]).collect();
+            let Some(choice) = least_loaded(&candidate_loads).map(|i| eligible[i]) else {
+                warn!(
+                    session_id = %placement.session_id,
+                    runner_id = %down.config.id,
+                    "No runner host to fail over session to"
+                );
+                continue;
+            };
+            let target = &self.hosts[choice];
+            self.placements
+                .set(&placement.session_id, &placement.user_id, &target.config.id)
+                .await?;
+            loads[choice].sessions += 1;
+            info!(
+                session_id = %placement.session_id,
+                from = %down.config.id,
+                to = %target.config.id,
+                "Failed over idle session"
+            );
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::Database;
+
+    fn host(id: &str, address: &str, users: &[&str]) -> RunnerHostConfig {
+        RunnerHostConfig {
+            id: id.to_string(),
+            address: address.to_string(),
+            token: "secret".to_string(),
+            capacity: 2,
+            users: users.iter().map(|u| u.to_string()).collect(),
+        }
+    }
+
+    #[test]
+    fn test_least_loaded() {
+        let load = |sessions, capacity| HostLoad { sessions, capacity };
+        assert_eq!(least_loaded(&[load(1, 4), load(1, 10)]), Some(1));
+        assert_eq!(least_loaded(&[load(2, 4), load(5, 10)]), Some(0));
+        assert_eq!(least_loaded(&[load(4, 4), load(9, 10)]), Some(1));
+        assert_eq!(least_loaded(&[load(4, 4), load(10, 10)]), None);
+        assert_eq!(least_loaded(&[]), None);
+    }
+
+    #[tokio::test]
+    async fn test_rejects_invalid_hosts() {
+        let db = Database::in_memory().await.unwrap();
+        let repo = || RunnerPlacementRepository::new(db.shared().clone());
+        let config = RunnerFederationConfig::default;
+        let mut tokenless = host("a", "10.0.0.1:7420", &[]);
+        tokenless.token.clear();
+        assert!(RunnerPool::new(vec![tokenless], config(), repo()).is_err());
+        assert!(
+            RunnerPool::new(
+                vec![
+                    host("a", LOCAL_RUNNER, &[]),
+                    host("a", "10.0.0.1:7420", &[])
+                ],
+                config(),
+                repo()
+            )
+            .is_err()
+        );
+        assert!(RunnerPool::new(Vec::new(), config(), repo()).is_err());
+
+        let pool = RunnerPool::new(
+            vec![
+                host("local", LOCAL_RUNNER, &[]),
+                host("a", "10.0.0.1:7420", &[]),
+            ],
+            config(),
+            repo(),
+        )
+        .unwrap();
+        assert!(pool.owns(&RunnerClient::tcp("10.0.0.1:7420", "secret")));
+        assert!(!pool.owns(&RunnerClient::new("/tmp/oqto-runner.sock")));
+    }
+
+    #[tokio::test]
+    async fn test_fail_over_idle_sessions() {
+        let db = Database::in_memory().await.unwrap();
+        let placements = RunnerPlacementRepository::new(db.shared().clone());
+        let pool = RunnerPool::new(
+            vec![
+                host("a", "10.0.0.1:7420", &[]),
+                host("b", "10.0.0.2:7420", &[]),
+                host("c", "10.0.0.3:7420", &["bob"]),
+            ],
+            RunnerFederationConfig::default(),
+            placements.clone(),
+        )
+        .unwrap();
+        placements.set("s1", "alice", "a").await.unwrap();
+        placements.set("s2", "alice", "a").await.unwrap();
+        placements.set("s3", "alice", "b").await.unwrap();
+        pool.hosts[0].health.lock().unwrap().healthy = false;
+
+        // s3 was not on the failed host, and c only takes bob's sessions.
+        let idle = vec!["s1".to_string(), "s2".to_string(), "s3".to_string()];
+        pool.fail_over(&pool.hosts[0], &idle).await.unwrap();
+        assert_eq!(placements.get("s1").await.unwrap().unwrap().runner_id, "b");
+        assert_eq!(placements.get("s2").await.unwrap().unwrap().runner_id, "b");
+        assert_eq!(placements.get("s3").await.unwrap().unwrap().runner_id, "b");
+
+        // Once b is full, sessions stay where they are.
+        pool.hosts[1].health.lock().unwrap().sessions = 2;
+        placements.set("s4", "alice", "a").await.unwrap();
+        pool.fail_over(&pool.hosts[0], &["s4".to_string()])
+            .await
+            .unwrap();
+        assert_eq!(placements.get("s4").await.unwrap().unwrap().runner_id, "a");
+
+        // A replica placing a session another one already placed follows it.
+        let first = placements.set_if_absent("s5", "alice", "a").await.unwrap();
+        let second = placements.set_if_absent("s5", "alice", "b").await.unwrap();
+        assert_eq!(first.runner_id, "a");
+        assert_eq!(second.runner_id, "a");
+
+        let home = RunnerClient::new("/tmp/home.sock");
+        let runner = pool
+            .runner_for_session("alice", "s1", false, &home)
+            .await
+            .unwrap();
+        assert_eq!(
+            runner.socket_path(),
+            std::path::Path::new("tcp://10.0.0.2:7420")
+        );
+        let runner = pool
+            .runner_for_session("alice", "unplaced", false, &home)
+            .await
+            .unwrap();
+        assert_eq!(runner.socket_path(), home.socket_path());
+    }
+}
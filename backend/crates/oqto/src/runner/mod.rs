@@ -30,6 +30,9 @@
 //!
 //! The runner daemon/runtime and socket client live in the `oqto-runner` crate.
 //! This server-side module only keeps backend-specific target routing because it
-//! depends on `AppState` and shared-workspace services.
+//! depends on `AppState` and shared-workspace services, plus the placement of
+//! sessions across federated runner hosts (`[[runners]]`, see [`federation`]).
 
+pub mod federation;
+pub mod placement;
 pub mod router;
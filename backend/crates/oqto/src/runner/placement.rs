@@ -0,0 +1,104 @@
+//! Persistence of session placements on federated runner hosts.
+//!
+//! Placements are kept with the shared tables, so with `[db] url` set every
+//! replica sends a session's commands to the same host.
+
+use anyhow::{Context, Result};
+
+use crate::db::{self, DbPool, on_pool};
+
+/// A session placed on a runner host.
+#[derive(Debug, Clone, PartialEq, Eq, sqlx::FromRow)]
+pub struct RunnerPlacement {
+    pub session_id: String,
+    pub user_id: String,
+    pub runner_id: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct RunnerPlacementRepository {
+    pool: DbPool,
+}
+
+impl RunnerPlacementRepository {
+    pub fn new(pool: DbPool) -> Self {
+        Self { pool }
+    }
+
+    pub async fn get(&self, session_id: &str) -> Result<Option<RunnerPlacement>> {
+        on_pool!(&self.pool, |pool| sqlx::query_as::<_, RunnerPlacement>(
+            "SELECT session_id, user_id, runner_id FROM runner_placements WHERE session_id = $1"
+        )
+        .bind(session_id)
+        .fetch_optional(pool)
+        .await)
+        .context("fetching runner placement")
+    }
+
+    /// Place a session on `runner_id`, replacing an earlier placement.
+    pub async fn set(&self, session_id: &str, user_id: &str, runner_id: &str) -> Result<()> {
+        on_pool!(&self.pool, |pool| sqlx::query(
+            r#"
+            INSERT INTO runner_placements (session_id, user_id, runner_id, created_at, updated_at)
+            VALUES ($1, $2, $3, $4, $4)
+            ON CONFLICT(session_id) DO UPDATE SET
+                runner_id = excluded.runner_id,
+                updated_at = excluded.updated_at
+            "#
+        )
+        .bind(session_id)
+        .bind(user_id)
+        .bind(runner_id)
+        .bind(db::now())
+        .execute(pool)
+        .await
+        .map(|_| ()))
+        .context("saving runner placement")
+    }
+
+    /// Place a session on `runner_id` unless it already has a placement, and
+    /// return the placement that holds. When replicas place the same session
+    /// at once, the first one wins.
+    pub async fn set_if_absent(
+        &self,
+        session_id: &str,
+        user_id: &str,
+        runner_id: &str,
+    ) -> Result<RunnerPlacement> {
+        on_pool!(&self.pool, |pool| sqlx::query(
+            r#"
+            INSERT INTO runner_placements (session_id, user_id, runner_id, created_at, updated_at)
+            VALUES ($1, $2, $3, $4, $4)
+            ON CONFLICT(session_id) DO NOTHING
+            "#
+        )
+        .bind(session_id)
+        .bind(user_id)
+        .bind(runner_id)
+        .bind(db::now())
+        .execute(pool)
+        .await
+        .map(|_| ()))
+        .context("saving runner placement")?;
+        self.get(session_id)
+            .await?
+            .context("runner placement vanished after saving it")
+    }
+
+    /// Placements of the given sessions on `runner_id`.
+    pub async fn on_runner(
+        &self,
+        runner_id: &str,
+        session_ids: &[String],
+    ) -> Result<Vec<RunnerPlacement>> {
+        let mut placements = Vec::new();
+        for session_id in session_ids {
+            if let Some(placement) = self.get(session_id).await?
+                && placement.runner_id == runner_id
+            {
+                placements.push(placement);
+            }
+        }
+        Ok(placements)
+    }
+}
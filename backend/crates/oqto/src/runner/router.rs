@@ -45,6 +45,28 @@ pub async fn resolve_runner_for_target(
     }
 }
 
+/// Resolve the runner a session runs on.
+///
+/// With federated runner hosts (`[[runners]]`), personal sessions run on the
+/// host they were placed on; `place` places a session that has no host yet.
+/// Shared workspace sessions always run on the workspace's runner.
+pub async fn resolve_runner_for_session(
+    state: &AppState,
+    user_id: &str,
+    session_id: &str,
+    target: &ExecutionTarget,
+    place: bool,
+) -> Result<Option<RunnerClient>> {
+    let runner = resolve_runner_for_target(state, user_id, target).await?;
+    match (&state.runner_pool, target, runner) {
+        (Some(pool), ExecutionTarget::Personal, Some(home)) => pool
+            .runner_for_session(user_id, session_id, place, &home)
+            .await
+            .map(Some),
+        (_, _, runner) => Ok(runner),
+    }
+}
+
 async fn ensure_runner_healthy(
     state: &AppState,
     linux_user: &str,
@@ -89,7 +111,7 @@ async fn ensure_runner_healthy(
     Ok(healed)
 }
 
-fn resolve_linux_uid(linux_user: &str) -> Result<u32> {
+pub(crate) fn resolve_linux_uid(linux_user: &str) -> Result<u32> {
     use std::process::Command;
 
     let output = Command::new("id")
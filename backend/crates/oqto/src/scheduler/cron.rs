@@ -0,0 +1,195 @@
+//! Minimal five-field cron matcher used to find runs missed while the server
+//! was down and to time agent schedules. Supports `*`, numbers, ranges, lists
+//! and `/step`; skdlr's shorthand forms are not cron and are never caught up.
+
+use chrono::{DateTime, Datelike, Duration, DurationRound, Timelike, Utc};
+
+/// How far ahead the next run is looked for. Four years cover a leap day.
+const NEXT_RUN_HORIZON_DAYS: i64 = 4 * 366;
+
+/// A parsed `minute hour day-of-month month day-of-week` expression.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CronSchedule {
+    minutes: u64,
+    hours: u64,
+    days: u64,
+    months: u64,
+    weekdays: u64,
+    /// Day-of-month and day-of-week were both restricted; cron then fires
+    /// when either matches.
+    either_day: bool,
+}
+
+impl CronSchedule {
+    /// Parse a cron expression. Returns None for anything else.
+    pub fn parse(expr: &str) -> Option<Self> {
+        let fields: Vec<&str> = expr.split_whitespace().collect();
+        if fields.len() != 5 {
+            return None;
+        }
+        let mut weekdays = parse_field(fields[4], 0, 7)?;
+        // 7 is Sunday as well.
+        if weekdays & (1 << 7) != 0 {
+            weekdays |= 1;
+        }
+        Some(Self {
+            minutes: parse_field(fields[0], 0, 59)?,
+            hours: parse_field(fields[1], 0, 23)?,
+            days: parse_field(fields[2], 1, 31)?,
+            months: parse_field(fields[3], 1, 12)?,
+            weekdays,
+            either_day: fields[2] != "*" && fields[4] != "*",
+        })
+    }
+
+    fn matches(&self, at: DateTime<Utc>) -> bool {
+        let day = self.days & (1 << at.day()) != 0;
+        let weekday = self.weekdays & (1 << at.weekday().num_days_from_sunday()) != 0;
+        let day_matches = if self.either_day {
+            day || weekday
+        } else {
+            day && weekday
+        };
+        self.minutes & (1 << at.minute()) != 0
+            && self.hours & (1 << at.hour()) != 0
+            && self.months & (1 << at.month()) != 0
+            && day_matches
+    }
+
+    /// Times the schedule fired after `after` up to and including `until`,
+    /// oldest first, at most `limit` of them.
+    pub fn occurrences(
+        &self,
+        after: DateTime<Utc>,
+        until: DateTime<Utc>,
+        limit: usize,
+    ) -> Vec<DateTime<Utc>> {
+        let mut found = Vec::new();
+        let Ok(start) = after.duration_trunc(Duration::minutes(1)) else {
+            return found;
+        };
+        let mut at = start + Duration::minutes(1);
+        while at <= until && found.len() < limit {
+            if self.matches(at) {
+                found.push(at);
+            }
+            at += Duration::minutes(1);
+        }
+        found
+    }
+
+    /// The first time the schedule fires after `after`. None when it never
+    /// does (`0 0 31 2 *`).
+    pub fn next_after(&self, after: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        self.occurrences(after, after + Duration::days(NEXT_RUN_HORIZON_DAYS), 1)
+            .into_iter()
+            .next()
+    }
+}
+
+fn parse_field(field: &str, min: u32, max: u32) -> Option<u64> {
+    let mut bits = 0u64;
+    for part in field.split(',') {
+        let (range, step) = match part.split_once('/') {
+            Some((range, step)) => (range, step.parse::<u32>().ok().filter(|s| *s > 0)?),
+            None => (part, 1),
+        };
+        let (lo, hi) = if range == "*" {
+            (min, max)
+        } else if let Some((lo, hi)) = range.split_once('-') {
+            (lo.parse().ok()?, hi.parse().ok()?)
+        } else {
+            let value: u32 = range.parse().ok()?;
+            // `5/15` means from 5 to the end of the range.
+            (value, if part.contains('/') { max } else { value })
+        };
+        if lo < min || hi > max || lo > hi {
+            return None;
+        }
+        for value in (lo..=hi).step_by(step as usize) {
+            bits |= 1 << value;
+        }
+    }
+    Some(bits)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(s: &str) -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339(s).unwrap().with_timezone(&Utc)
+    }
+
+    #[test]
+    fn test_parse_rejects_non_cron() {
+        assert!(CronSchedule::parse("every 1h").is_none());
+        assert!(CronSchedule::parse("0 2 * *").is_none());
+        assert!(CronSchedule::parse("60 * * * *").is_none());
+        assert!(CronSchedule::parse("*/0 * * * *").is_none());
+        assert!(CronSchedule::parse("0 2 * * *").is_some());
+    }
+
+    #[test]
+    fn test_occurrences_between() {
+        let daily = CronSchedule::parse("0 2 * * *").unwrap();
+        let missed = daily.occurrences(at("2026-05-10T03:00:00Z"), at("2026-05-13T02:00:00Z"), 100);
+        assert_eq!(
+            missed,
+            vec![
+                at("2026-05-11T02:00:00Z"),
+                at("2026-05-12T02:00:00Z"),
+                at("2026-05-13T02:00:00Z"),
+            ]
+        );
+
+        let quarter = CronSchedule::parse("*/15 9-10 * * 1-5").unwrap();
+        // Saturday: nothing; Monday 09:00-10:45: eight runs, capped at five.
+        assert!(
+            quarter
+                .occurrences(at("2026-05-16T00:00:00Z"), at("2026-05-16T23:59:00Z"), 100)
+                .is_empty()
+        );
+        assert_eq!(
+            quarter
+                .occurrences(at("2026-05-18T00:00:00Z"), at("2026-05-18T23:59:00Z"), 100)
+                .len(),
+            8
+        );
+        assert_eq!(
+            quarter
+                .occurrences(at("2026-05-18T00:00:00Z"), at("2026-05-18T23:59:00Z"), 5)
+                .len(),
+            5
+        );
+    }
+
+    #[test]
+    fn test_next_after() {
+        let nightly = CronSchedule::parse("30 3 * * *").unwrap();
+        assert_eq!(
+            nightly.next_after(at("2026-05-13T03:30:00Z")),
+            Some(at("2026-05-14T03:30:00Z"))
+        );
+        let leap_day = CronSchedule::parse("0 0 29 2 *").unwrap();
+        assert_eq!(
+            leap_day.next_after(at("2026-05-13T00:00:00Z")),
+            Some(at("2028-02-29T00:00:00Z"))
+        );
+        assert!(
+            CronSchedule::parse("0 0 31 2 *")
+                .unwrap()
+                .next_after(at("2026-05-13T00:00:00Z"))
+                .is_none()
+        );
+    }
+
+    #[test]
+    fn test_day_of_month_or_weekday() {
+        // The 1st of the month or any Sunday.
+        let cron = CronSchedule::parse("0 0 1 * 7").unwrap();
+        assert!(cron.matches(at("2026-05-01T00:00:00Z")));
+        assert!(cron.matches(at("2026-05-03T00:00:00Z")));
+        assert!(!cron.matches(at("2026-05-04T00:00:00Z")));
+    }
+}
@@ -0,0 +1,488 @@
+//! Run history for skdlr schedules.
+//!
+//! skdlr runs schedules on its own; it (or a wrapper around the scheduled
+//! command) reports each run's start and result to
+//! `POST /api/schedules/{name}/runs`. The backend keeps that history, tells
+//! the user when a schedule keeps failing, and records a heartbeat while it
+//! is up so cron runs that were due during downtime can be accounted for as
+//! skipped on the next start (and optionally caught up).
+//!
+//! Agent schedules are run by the backend itself: when one is due, a new
+//! session is started in its workspace, sent its prompt and closed once the
+//! agent is idle again (see `api::handlers::run_agent_schedules`). Their runs
+//! land in the same history.
+
+mod cron;
+mod models;
+mod repository;
+
+pub use models::{
+    AgentSchedule, CatchUpPolicy, SaveAgentScheduleRequest, ScheduleRunPage, ScheduleRunQuery,
+    ScheduleRunRecord, ScheduleRunReport, ScheduleRunStatus, SchedulerConfig,
+};
+pub use repository::{AgentScheduleRepository, ScheduleRunRepository};
+
+use std::collections::HashSet;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use anyhow::{Result, bail};
+use chrono::{DateTime, Utc};
+use tracing::{debug, info, warn};
+
+use crate::ws::{WsEvent, WsHub};
+
+use cron::CronSchedule;
+
+/// Skipped runs recorded per schedule and downtime, at most.
+const MAX_SKIPPED_PER_SCHEDULE: usize = 1000;
+/// How often old run history is pruned.
+const MAINTENANCE_INTERVAL: Duration = Duration::from_secs(3600);
+
+/// Runs of one schedule that were due while the server was down.
+#[derive(Debug, Clone)]
+pub struct MissedRuns {
+    pub user_id: String,
+    pub schedule_name: String,
+    pub count: usize,
+}
+
+/// An agent schedule run that is due now.
+#[derive(Debug, Clone)]
+pub struct DueAgentRun {
+    pub schedule: AgentSchedule,
+    pub scheduled_for: DateTime<Utc>,
+}
+
+/// The next run of an agent schedule's cron expression after `after`.
+pub fn next_cron_run(expr: &str, after: DateTime<Utc>) -> Result<DateTime<Utc>> {
+    let Some(cron) = CronSchedule::parse(expr) else {
+        bail!("'{expr}' is not a five-field cron expression");
+    };
+    match cron.next_after(after) {
+        Some(next) => Ok(next),
+        None => bail!("'{expr}' never fires"),
+    }
+}
+
+/// Scheduler run history service.
+pub struct SchedulerService {
+    repo: ScheduleRunRepository,
+    agent_schedules: AgentScheduleRepository,
+    config: SchedulerConfig,
+    hub: Arc<WsHub>,
+    /// Agent schedules with a run in progress, by ID.
+    running: Mutex<HashSet<String>>,
+}
+
+impl SchedulerService {
+    pub fn new(
+        repo: ScheduleRunRepository,
+        agent_schedules: AgentScheduleRepository,
+        config: SchedulerConfig,
+        hub: Arc<WsHub>,
+    ) -> Self {
+        Self {
+            repo,
+            agent_schedules,
+            config,
+            hub,
+            running: Mutex::new(HashSet::new()),
+        }
+    }
+
+    pub fn repository(&self) -> &ScheduleRunRepository {
+        &self.repo
+    }
+
+    pub fn agent_schedules(&self) -> &AgentScheduleRepository {
+        &self.agent_schedules
+    }
+
+    pub fn config(&self) -> &SchedulerConfig {
+        &self.config
+    }
+
+    /// Claim an agent schedule for a run. False while a run is in progress.
+    pub fn begin_agent_run(&self, schedule_id: &str) -> bool {
+        self.running
+            .lock()
+            .expect("running schedules lock")
+            .insert(schedule_id.to_string())
+    }
+
+    pub fn end_agent_run(&self, schedule_id: &str) {
+        self.running
+            .lock()
+            .expect("running schedules lock")
+            .remove(schedule_id);
+    }
+
+    /// Agent schedules due at `now`, each moved on to its next run.
+    ///
+    /// Each due run is claimed in the database before it is taken, so with
+    /// several replicas polling the same schedules only one of them runs it.
+    /// Occurrences missed while the server was down (or the poll loop was
+    /// behind) are recorded as skipped. Only the latest due occurrence runs,
+    /// and with `catch_up = "skip"` only when it is not older than two poll
+    /// intervals.
+    pub async fn take_due_agent_runs(&self, now: DateTime<Utc>) -> Result<Vec<DueAgentRun>> {
+        let grace =
+            chrono::Duration::seconds((2 * self.config.agent_poll_interval_seconds).max(60) as i64);
+        let horizon = now - chrono::Duration::hours(self.config.max_catch_up_hours as i64);
+
+        let mut runs = Vec::new();
+        for schedule in self.agent_schedules.due(now).await? {
+            let Some(due_at) = schedule.next_run_at.clone() else {
+                continue;
+            };
+            let parsed = CronSchedule::parse(&schedule.cron).zip(parse_ts(&due_at));
+            let next = parsed.as_ref().and_then(|(cron, _)| cron.next_after(now));
+            if !self
+                .agent_schedules
+                .claim(&schedule.id, &due_at, next)
+                .await?
+            {
+                continue;
+            }
+            let Some((cron, first)) = parsed else {
+                continue;
+            };
+            let mut due = vec![first];
+            due.extend(cron.occurrences(first.max(horizon), now, MAX_SKIPPED_PER_SCHEDULE));
+            let latest = due.pop().unwrap_or(first);
+            let run = now - latest <= grace || self.config.catch_up == CatchUpPolicy::RunOnce;
+            if !run {
+                due.push(latest);
+            }
+            if !due.is_empty() {
+                self.repo
+                    .record_skipped(&schedule.user_id, &schedule.name, &due)
+                    .await?;
+            }
+            if run {
+                runs.push(DueAgentRun {
+                    schedule,
+                    scheduled_for: latest,
+                });
+            }
+        }
+        Ok(runs)
+    }
+
+    /// Record a reported run and notify the user when it completes a
+    /// failure streak. Returns None when the run ID belongs to another
+    /// schedule.
+    pub async fn record_run(
+        &self,
+        user_id: &str,
+        schedule_name: &str,
+        report: &ScheduleRunReport,
+    ) -> Result<Option<ScheduleRunRecord>> {
+        let Some(record) = self
+            .repo
+            .upsert_run(user_id, schedule_name, report, Utc::now())
+            .await?
+        else {
+            return Ok(None);
+        };
+
+        if record.status == ScheduleRunStatus::Failed {
+            let streak = self.repo.failure_streak(user_id, schedule_name).await?;
+            // Notify once per streak, when it reaches the threshold.
+            if streak == self.config.failure_streak_threshold {
+                self.notify_failure_streak(&record, streak).await;
+            }
+        }
+        Ok(Some(record))
+    }
+
+    async fn notify_failure_streak(&self, record: &ScheduleRunRecord, streak: i64) {
+        warn!(
+            user_id = %record.user_id,
+            schedule = %record.schedule_name,
+            streak,
+            "Scheduled job keeps failing"
+        );
+        self.hub
+            .send_to_user(
+                &record.user_id,
+                WsEvent::Notification {
+                    level: "warning".to_string(),
+                    title: format!("Schedule `{}` keeps failing", record.schedule_name),
+                    message: format!(
+                        "The last {streak} runs failed. Check the job's logs \
+                         (`skdlr logs {}`).",
+                        record.schedule_name
+                    ),
+                    category: "scheduler.failure_streak".to_string(),
+                    detail: Some(serde_json::json!({
+                        "schedule_name": record.schedule_name,
+                        "failure_streak": streak,
+                        "run_id": record.id,
+                        "exit_code": record.exit_code,
+                        "log_path": record.log_path,
+                    })),
+                },
+            )
+            .await;
+    }
+
+    /// Remember a user's current schedules (name, expression) for downtime
+    /// accounting.
+    pub async fn sync_schedules(&self, user_id: &str, schedules: &[(String, String)]) {
+        if let Err(e) = self
+            .repo
+            .sync_definitions(user_id, schedules, Utc::now())
+            .await
+        {
+            warn!(user_id = %user_id, "Failed to store schedule definitions: {e:#}");
+        }
+    }
+
+    /// Record cron runs that were due between the last heartbeat and `now`
+    /// as skipped. Must run before the heartbeat task starts.
+    pub async fn account_downtime(&self, now: DateTime<Utc>) -> Result<Vec<MissedRuns>> {
+        let Some(down_since) = self
+            .repo
+            .last_heartbeat()
+            .await?
+            .as_deref()
+            .and_then(parse_ts)
+        else {
+            return Ok(Vec::new());
+        };
+        let horizon = now - chrono::Duration::hours(self.config.max_catch_up_hours as i64);
+        let down_since = down_since.max(horizon);
+
+        let mut missed = Vec::new();
+        for def in self.repo.definitions().await? {
+            let Some(cron) = CronSchedule::parse(&def.schedule) else {
+                continue;
+            };
+            // A run reported after the server went down covers earlier runs.
+            let last_run = self
+                .repo
+                .last_run_at(&def.user_id, &def.name)
+                .await?
+                .as_deref()
+                .and_then(parse_ts);
+            let after = last_run.map_or(down_since, |last| last.max(down_since));
+            let due = cron.occurrences(after, now, MAX_SKIPPED_PER_SCHEDULE);
+            if due.is_empty() {
+                continue;
+            }
+            let count = self
+                .repo
+                .record_skipped(&def.user_id, &def.name, &due)
+                .await?;
+            if count > 0 {
+                missed.push(MissedRuns {
+                    user_id: def.user_id,
+                    schedule_name: def.name,
+                    count,
+                });
+            }
+        }
+
+        if !missed.is_empty() {
+            info!(
+                schedules = missed.len(),
+                runs = missed.iter().map(|m| m.count).sum::<usize>(),
+                "Recorded scheduled runs missed while the server was down"
+            );
+        }
+        Ok(missed)
+    }
+
+    /// Record a heartbeat periodically and prune old run history.
+    pub fn start_heartbeat_task(self: &Arc<Self>) -> tokio::task::JoinHandle<()> {
+        let service = Arc::clone(self);
+        tokio::spawn(async move {
+            let mut heartbeat = tokio::time::interval(Duration::from_secs(
+                service.config.heartbeat_interval_seconds.max(1),
+            ));
+            let mut maintenance = tokio::time::interval(MAINTENANCE_INTERVAL);
+            loop {
+                tokio::select! {
+                    _ = heartbeat.tick() => {
+                        if let Err(e) = service.repo.heartbeat(Utc::now()).await {
+                            warn!("Failed to record scheduler heartbeat: {e:#}");
+                        }
+                    }
+                    _ = maintenance.tick() => {
+                        match service
+                            .repo
+                            .prune(service.config.run_retention_days, Utc::now())
+                            .await
+                        {
+                            Ok(0) => {}
+                            Ok(n) => debug!("Pruned {n} schedule runs"),
+                            Err(e) => warn!("Failed to prune schedule runs: {e:#}"),
+                        }
+                    }
+                }
+            }
+        })
+    }
+}
+
+fn parse_ts(ts: &str) -> Option<DateTime<Utc>> {
+    DateTime::parse_from_rfc3339(ts)
+        .ok()
+        .map(|t| t.with_timezone(&Utc))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::Database;
+
+    async fn database() -> Database {
+        let db = Database::in_memory().await.unwrap();
+        sqlx::query("INSERT INTO users (id, username, email, display_name) VALUES (?, ?, ?, ?)")
+            .bind("alice")
+            .bind("alice")
+            .bind("alice@example.com")
+            .bind("Alice")
+            .execute(db.pool())
+            .await
+            .unwrap();
+        db
+    }
+
+    fn service(db: &Database) -> SchedulerService {
+        SchedulerService::new(
+            ScheduleRunRepository::new(db.shared().clone()),
+            AgentScheduleRepository::new(db.shared().clone()),
+            SchedulerConfig::default(),
+            Arc::new(WsHub::new()),
+        )
+    }
+
+    #[tokio::test]
+    async fn test_account_downtime() {
+        let db = database().await;
+        let service = service(&db);
+        let now = parse_ts("2026-05-13T12:00:00Z").unwrap();
+
+        // First start: nothing to account for.
+        assert!(service.account_downtime(now).await.unwrap().is_empty());
+
+        service
+            .sync_schedules(
+                "alice",
+                &[
+                    ("hourly".to_string(), "0 * * * *".to_string()),
+                    ("adhoc".to_string(), "once 2026-05-13 09:00".to_string()),
+                ],
+            )
+            .await;
+        service
+            .repo
+            .heartbeat(parse_ts("2026-05-13T08:30:00Z").unwrap())
+            .await
+            .unwrap();
+
+        let missed = service.account_downtime(now).await.unwrap();
+        assert_eq!(missed.len(), 1);
+        assert_eq!(missed[0].schedule_name, "hourly");
+        // 09:00, 10:00, 11:00 and 12:00.
+        assert_eq!(missed[0].count, 4);
+
+        // Accounting again does not duplicate skipped runs.
+        assert!(service.account_downtime(now).await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_due_agent_runs() {
+        let db = database().await;
+        let service = service(&db);
+        let request = SaveAgentScheduleRequest {
+            name: None,
+            cron: "0 * * * *".to_string(),
+            workspace_path: "/home/alice/project".to_string(),
+            prompt: "Update dependencies".to_string(),
+            template: None,
+            harness: None,
+            provider: None,
+            model: None,
+            timeout_secs: None,
+            enabled: true,
+        };
+        let created = parse_ts("2026-05-13T08:30:00Z").unwrap();
+        let next = next_cron_run(&request.cron, created).unwrap();
+        assert_eq!(next, parse_ts("2026-05-13T09:00:00Z").unwrap());
+        service
+            .agent_schedules
+            .upsert("alice", "deps", &request, Some(next), created)
+            .await
+            .unwrap();
+
+        // Not due yet.
+        let early = parse_ts("2026-05-13T08:59:00Z").unwrap();
+        assert!(service.take_due_agent_runs(early).await.unwrap().is_empty());
+
+        // On time: runs.
+        let on_time = parse_ts("2026-05-13T09:00:20Z").unwrap();
+        let runs = service.take_due_agent_runs(on_time).await.unwrap();
+        assert_eq!(runs.len(), 1);
+        assert_eq!(runs[0].scheduled_for, next);
+        // A replica that saw the run due before it was claimed does not get it.
+        let seen_due = runs[0].schedule.next_run_at.as_deref().unwrap();
+        assert!(
+            !service
+                .agent_schedules
+                .claim(&runs[0].schedule.id, seen_due, None)
+                .await
+                .unwrap()
+        );
+        assert!(
+            service
+                .take_due_agent_runs(on_time)
+                .await
+                .unwrap()
+                .is_empty()
+        );
+
+        // Down from 09:30 to 12:30: 10:00, 11:00 and 12:00 are skipped.
+        let after_downtime = parse_ts("2026-05-13T12:30:00Z").unwrap();
+        assert!(
+            service
+                .take_due_agent_runs(after_downtime)
+                .await
+                .unwrap()
+                .is_empty()
+        );
+        let (skipped, total) = service
+            .repo
+            .list(
+                "alice",
+                "deps",
+                &ScheduleRunQuery {
+                    status: Some(ScheduleRunStatus::Skipped),
+                    ..Default::default()
+                },
+            )
+            .await
+            .unwrap();
+        assert_eq!(total, 3);
+        assert_eq!(
+            skipped[0].scheduled_for.as_deref(),
+            Some("2026-05-13T12:00:00.000Z")
+        );
+        let schedule = service
+            .agent_schedules
+            .get("alice", "deps")
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            schedule.next_run_at.as_deref(),
+            Some("2026-05-13T13:00:00.000Z")
+        );
+
+        assert!(next_cron_run("@daily", created).is_err());
+        assert!(next_cron_run("0 0 31 2 *", created).is_err());
+    }
+}
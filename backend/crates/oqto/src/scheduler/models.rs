@@ -0,0 +1,190 @@
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+
+/// Scheduler run history configuration.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SchedulerConfig {
+    /// Record run history and account for runs missed during downtime.
+    pub enabled: bool,
+    /// What to do with cron runs that were due while the server was down.
+    pub catch_up: CatchUpPolicy,
+    /// Downtime longer than this is only accounted for its most recent part.
+    pub max_catch_up_hours: u64,
+    /// Consecutive failed runs of a schedule before the user is notified.
+    pub failure_streak_threshold: i64,
+    /// How often the server records that it is up.
+    pub heartbeat_interval_seconds: u64,
+    /// How long run history is kept.
+    pub run_retention_days: i64,
+    /// How often agent schedules are checked for due runs.
+    pub agent_poll_interval_seconds: u64,
+    /// Agent runs still working after this long are stopped and failed,
+    /// unless the schedule sets its own timeout.
+    pub agent_run_timeout_secs: u64,
+    /// Agent schedules a user can have.
+    pub max_agent_schedules_per_user: i64,
+}
+
+impl Default for SchedulerConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            catch_up: CatchUpPolicy::Skip,
+            max_catch_up_hours: 168,
+            failure_streak_threshold: 3,
+            heartbeat_interval_seconds: 60,
+            run_retention_days: 90,
+            agent_poll_interval_seconds: 30,
+            agent_run_timeout_secs: 3600,
+            max_agent_schedules_per_user: 50,
+        }
+    }
+}
+
+/// Handling of runs that were due while the server was down.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CatchUpPolicy {
+    /// Record them as skipped.
+    #[default]
+    Skip,
+    /// Record them as skipped and run each affected schedule once now.
+    RunOnce,
+}
+
+/// Outcome of a scheduled run.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, sqlx::Type)]
+#[serde(rename_all = "snake_case")]
+#[sqlx(type_name = "text", rename_all = "snake_case")]
+pub enum ScheduleRunStatus {
+    Running,
+    Succeeded,
+    Failed,
+    /// Due while the server was down and not run.
+    Skipped,
+}
+
+/// One run of a schedule.
+#[derive(Debug, Clone, Serialize, FromRow)]
+pub struct ScheduleRunRecord {
+    pub id: String,
+    pub user_id: String,
+    pub schedule_name: String,
+    pub status: ScheduleRunStatus,
+    /// When the run was due (RFC 3339), if known.
+    pub scheduled_for: Option<String>,
+    pub started_at: Option<String>,
+    pub ended_at: Option<String>,
+    pub exit_code: Option<i32>,
+    /// Where the scheduler keeps the run's output.
+    pub log_path: Option<String>,
+    /// LLM spend of agentic runs.
+    pub cost_usd: Option<f64>,
+    pub error: Option<String>,
+    /// Session an agent schedule run used.
+    pub session_id: Option<String>,
+    /// Started, or due for skipped runs. Runs are ordered by this.
+    pub run_at: String,
+}
+
+/// Run start or result reported by the scheduler. Reporting the same `id`
+/// again updates the run.
+#[derive(Debug, Clone, Deserialize)]
+pub struct ScheduleRunReport {
+    #[serde(default)]
+    pub id: Option<String>,
+    pub status: ScheduleRunStatus,
+    #[serde(default)]
+    pub scheduled_for: Option<String>,
+    #[serde(default)]
+    pub started_at: Option<String>,
+    #[serde(default)]
+    pub ended_at: Option<String>,
+    #[serde(default)]
+    pub exit_code: Option<i32>,
+    #[serde(default)]
+    pub log_path: Option<String>,
+    #[serde(default)]
+    pub cost_usd: Option<f64>,
+    #[serde(default)]
+    pub error: Option<String>,
+    #[serde(default)]
+    pub session_id: Option<String>,
+}
+
+/// Pagination and filters for a schedule's run history.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct ScheduleRunQuery {
+    #[serde(default)]
+    pub status: Option<ScheduleRunStatus>,
+    #[serde(default)]
+    pub limit: Option<i64>,
+    #[serde(default)]
+    pub offset: Option<i64>,
+}
+
+/// A page of run history, newest first.
+#[derive(Debug, Clone, Serialize)]
+pub struct ScheduleRunPage {
+    pub runs: Vec<ScheduleRunRecord>,
+    /// Runs matching the filters across all pages.
+    pub total: i64,
+    pub limit: i64,
+    pub offset: i64,
+    /// Consecutive failures up to the latest finished run.
+    pub failure_streak: i64,
+}
+
+/// A prompt the backend runs in a fresh session on a cron schedule.
+#[derive(Debug, Clone, Serialize, FromRow)]
+pub struct AgentSchedule {
+    pub id: String,
+    pub user_id: String,
+    pub name: String,
+    /// Five-field cron expression (UTC).
+    pub cron: String,
+    pub workspace_path: String,
+    /// Sent as the first message, or as the arguments of `template`.
+    pub prompt: String,
+    /// Prompt template invoked as `/<template> <prompt>`.
+    pub template: Option<String>,
+    pub harness: Option<String>,
+    pub provider: Option<String>,
+    pub model: Option<String>,
+    /// Overrides `agent_run_timeout_secs`.
+    pub timeout_secs: Option<i64>,
+    pub enabled: bool,
+    /// Next due run (RFC 3339); None while disabled.
+    pub next_run_at: Option<String>,
+    pub last_run_at: Option<String>,
+    pub created_at: String,
+    pub updated_at: String,
+}
+
+/// Body of `POST /api/schedules` and `PUT /api/schedules/{name}`.
+#[derive(Debug, Clone, Deserialize)]
+pub struct SaveAgentScheduleRequest {
+    /// Taken from the path on update.
+    #[serde(default)]
+    pub name: Option<String>,
+    pub cron: String,
+    pub workspace_path: String,
+    pub prompt: String,
+    #[serde(default)]
+    pub template: Option<String>,
+    #[serde(default)]
+    pub harness: Option<String>,
+    #[serde(default)]
+    pub provider: Option<String>,
+    #[serde(default)]
+    pub model: Option<String>,
+    #[serde(default)]
+    pub timeout_secs: Option<i64>,
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+}
+
+fn default_enabled() -> bool {
+    true
+}
@@ -0,0 +1,622 @@
+use anyhow::{Context, Result};
+use chrono::{DateTime, SecondsFormat, Utc};
+
+use crate::db::{self, DbPool, on_pool};
+
+use super::models::{
+    AgentSchedule, SaveAgentScheduleRequest, ScheduleRunQuery, ScheduleRunRecord,
+    ScheduleRunReport, ScheduleRunStatus,
+};
+
+const RUN_COLUMNS: &str = "id, user_id, schedule_name, status, scheduled_for, started_at, \
+     ended_at, exit_code, log_path, cost_usd, error, session_id, run_at";
+
+const AGENT_SCHEDULE_COLUMNS: &str = "id, user_id, name, cron, workspace_path, prompt, template, \
+     harness, provider, model, timeout_secs, enabled, next_run_at, last_run_at, created_at, \
+     updated_at";
+
+/// Finished runs looked at when computing a failure streak.
+const STREAK_SCAN_LIMIT: i64 = 1000;
+
+/// Schedule expression last seen for a user's schedule.
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct ScheduleDefinition {
+    pub user_id: String,
+    pub name: String,
+    pub schedule: String,
+}
+
+pub(super) fn timestamp(at: DateTime<Utc>) -> String {
+    at.to_rfc3339_opts(SecondsFormat::Millis, true)
+}
+
+/// Reported timestamps are stored in one format so they sort as text.
+fn normalize(ts: &Option<String>) -> Option<String> {
+    ts.as_ref().map(|ts| {
+        DateTime::parse_from_rfc3339(ts)
+            .map(|t| timestamp(t.with_timezone(&Utc)))
+            .unwrap_or_else(|_| ts.clone())
+    })
+}
+
+#[derive(Debug, Clone)]
+pub struct ScheduleRunRepository {
+    pool: DbPool,
+}
+
+impl ScheduleRunRepository {
+    pub fn new(pool: DbPool) -> Self {
+        Self { pool }
+    }
+
+    /// Insert a reported run, or update it when `report.id` is already
+    /// known. Returns None when the ID belongs to another schedule.
+    pub async fn upsert_run(
+        &self,
+        user_id: &str,
+        schedule_name: &str,
+        report: &ScheduleRunReport,
+        now: DateTime<Utc>,
+    ) -> Result<Option<ScheduleRunRecord>> {
+        let id = report
+            .id
+            .clone()
+            .unwrap_or_else(|| format!("run_{}", uuid::Uuid::new_v4().simple()));
+        let scheduled_for = normalize(&report.scheduled_for);
+        let started_at = normalize(&report.started_at);
+        let ended_at = normalize(&report.ended_at);
+        let run_at = started_at
+            .clone()
+            .or_else(|| scheduled_for.clone())
+            .unwrap_or_else(|| timestamp(now));
+
+        let sql = format!(
+            r#"INSERT INTO schedule_runs
+                   (id, user_id, schedule_name, status, scheduled_for, started_at, ended_at,
+                    exit_code, log_path, cost_usd, error, session_id, run_at)
+               VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13)
+               ON CONFLICT (user_id, id) DO UPDATE SET
+                   status = excluded.status,
+                   scheduled_for = COALESCE(excluded.scheduled_for, schedule_runs.scheduled_for),
+                   started_at = COALESCE(excluded.started_at, schedule_runs.started_at),
+                   ended_at = COALESCE(excluded.ended_at, schedule_runs.ended_at),
+                   exit_code = COALESCE(excluded.exit_code, schedule_runs.exit_code),
+                   log_path = COALESCE(excluded.log_path, schedule_runs.log_path),
+                   cost_usd = COALESCE(excluded.cost_usd, schedule_runs.cost_usd),
+                   error = COALESCE(excluded.error, schedule_runs.error),
+                   session_id = COALESCE(excluded.session_id, schedule_runs.session_id)
+               WHERE schedule_runs.schedule_name = excluded.schedule_name
+               RETURNING {RUN_COLUMNS}"#
+        );
+        on_pool!(&self.pool, |pool| sqlx::query_as::<_, ScheduleRunRecord>(
+            &sql
+        )
+        .bind(&id)
+        .bind(user_id)
+        .bind(schedule_name)
+        .bind(report.status)
+        .bind(&scheduled_for)
+        .bind(&started_at)
+        .bind(&ended_at)
+        .bind(report.exit_code)
+        .bind(&report.log_path)
+        .bind(report.cost_usd)
+        .bind(&report.error)
+        .bind(&report.session_id)
+        .bind(&run_at)
+        .fetch_optional(pool)
+        .await)
+        .context("upsert schedule run")
+    }
+
+    /// Record runs that were due at `due` but never ran. Already recorded
+    /// occurrences are ignored. Returns how many were new.
+    pub async fn record_skipped(
+        &self,
+        user_id: &str,
+        schedule_name: &str,
+        due: &[DateTime<Utc>],
+    ) -> Result<usize> {
+        let mut inserted = 0;
+        for at in due {
+            let at = timestamp(*at);
+            let id = format!("skip_{schedule_name}_{at}");
+            let rows = on_pool!(&self.pool, |pool| sqlx::query(
+                r#"INSERT INTO schedule_runs
+                       (id, user_id, schedule_name, status, scheduled_for, run_at)
+                   VALUES ($1, $2, $3, $4, $5, $5)
+                   ON CONFLICT DO NOTHING"#
+            )
+            .bind(&id)
+            .bind(user_id)
+            .bind(schedule_name)
+            .bind(ScheduleRunStatus::Skipped)
+            .bind(&at)
+            .execute(pool)
+            .await
+            .map(|r| r.rows_affected()))
+            .context("insert skipped schedule run")?;
+            inserted += rows as usize;
+        }
+        Ok(inserted)
+    }
+
+    /// A page of a schedule's runs, newest first, and the total matching.
+    pub async fn list(
+        &self,
+        user_id: &str,
+        schedule_name: &str,
+        query: &ScheduleRunQuery,
+    ) -> Result<(Vec<ScheduleRunRecord>, i64)> {
+        let total: i64 = on_pool!(&self.pool, |pool| {
+            let mut qb =
+                db::query_builder(pool, "SELECT COUNT(*) FROM schedule_runs WHERE user_id = ");
+            qb.push_bind(user_id)
+                .push(" AND schedule_name = ")
+                .push_bind(schedule_name);
+            if let Some(status) = query.status {
+                qb.push(" AND status = ").push_bind(status);
+            }
+            qb.build_query_scalar().fetch_one(pool).await
+        })
+        .context("count schedule runs")?;
+
+        let runs = on_pool!(&self.pool, |pool| {
+            let mut qb = db::query_builder(
+                pool,
+                format!("SELECT {RUN_COLUMNS} FROM schedule_runs WHERE user_id = "),
+            );
+            qb.push_bind(user_id)
+                .push(" AND schedule_name = ")
+                .push_bind(schedule_name);
+            if let Some(status) = query.status {
+                qb.push(" AND status = ").push_bind(status);
+            }
+            qb.push(" ORDER BY run_at DESC, id DESC LIMIT ")
+                .push_bind(query.limit.unwrap_or(50).clamp(1, 500))
+                .push(" OFFSET ")
+                .push_bind(query.offset.unwrap_or(0).max(0));
+            qb.build_query_as::<ScheduleRunRecord>()
+                .fetch_all(pool)
+                .await
+        })
+        .context("list schedule runs")?;
+
+        Ok((runs, total))
+    }
+
+    /// Consecutive failed runs up to the latest succeeded or failed one.
+    /// Skipped and running runs do not break a streak.
+    pub async fn failure_streak(&self, user_id: &str, schedule_name: &str) -> Result<i64> {
+        let statuses: Vec<ScheduleRunStatus> = on_pool!(&self.pool, |pool| sqlx::query_scalar(
+            r#"SELECT status FROM schedule_runs
+               WHERE user_id = $1 AND schedule_name = $2 AND status IN ('succeeded', 'failed')
+               ORDER BY run_at DESC, id DESC
+               LIMIT $3"#
+        )
+        .bind(user_id)
+        .bind(schedule_name)
+        .bind(STREAK_SCAN_LIMIT)
+        .fetch_all(pool)
+        .await)
+        .context("query schedule failure streak")?;
+
+        Ok(statuses
+            .iter()
+            .take_while(|s| **s == ScheduleRunStatus::Failed)
+            .count() as i64)
+    }
+
+    /// Latest `run_at` of a schedule, if it has any runs.
+    pub async fn last_run_at(&self, user_id: &str, schedule_name: &str) -> Result<Option<String>> {
+        on_pool!(&self.pool, |pool| sqlx::query_scalar(
+            "SELECT MAX(run_at) FROM schedule_runs WHERE user_id = $1 AND schedule_name = $2"
+        )
+        .bind(user_id)
+        .bind(schedule_name)
+        .fetch_one(pool)
+        .await)
+        .context("query last schedule run")
+    }
+
+    /// Remember the expressions of a user's schedules. Schedules missing
+    /// from `schedules` were removed and are forgotten.
+    pub async fn sync_definitions(
+        &self,
+        user_id: &str,
+        schedules: &[(String, String)],
+        now: DateTime<Utc>,
+    ) -> Result<()> {
+        let now = timestamp(now);
+        on_pool!(&self.pool, |pool| {
+            let mut tx = pool.begin().await?;
+            sqlx::query("DELETE FROM schedule_definitions WHERE user_id = $1")
+                .bind(user_id)
+                .execute(&mut *tx)
+                .await?;
+            for (name, schedule) in schedules {
+                sqlx::query(
+                    "INSERT INTO schedule_definitions (user_id, name, schedule, updated_at) \
+                     VALUES ($1, $2, $3, $4)",
+                )
+                .bind(user_id)
+                .bind(name)
+                .bind(schedule)
+                .bind(&now)
+                .execute(&mut *tx)
+                .await?;
+            }
+            tx.commit().await
+        })
+        .context("store schedule definitions")
+    }
+
+    pub async fn definitions(&self) -> Result<Vec<ScheduleDefinition>> {
+        on_pool!(&self.pool, |pool| sqlx::query_as::<_, ScheduleDefinition>(
+            "SELECT user_id, name, schedule FROM schedule_definitions ORDER BY user_id, name"
+        )
+        .fetch_all(pool)
+        .await)
+        .context("list schedule definitions")
+    }
+
+    /// Last recorded heartbeat, if the server has run before.
+    pub async fn last_heartbeat(&self) -> Result<Option<String>> {
+        on_pool!(&self.pool, |pool| sqlx::query_scalar(
+            "SELECT beat_at FROM scheduler_heartbeat WHERE id = 1"
+        )
+        .fetch_optional(pool)
+        .await)
+        .context("query scheduler heartbeat")
+    }
+
+    pub async fn heartbeat(&self, now: DateTime<Utc>) -> Result<()> {
+        let now = timestamp(now);
+        on_pool!(&self.pool, |pool| sqlx::query(
+            "INSERT INTO scheduler_heartbeat (id, beat_at) VALUES (1, $1) \
+             ON CONFLICT (id) DO UPDATE SET beat_at = excluded.beat_at"
+        )
+        .bind(&now)
+        .execute(pool)
+        .await
+        .map(|_| ()))
+        .context("record scheduler heartbeat")
+    }
+
+    /// Delete runs older than `retention_days`.
+    pub async fn prune(&self, retention_days: i64, now: DateTime<Utc>) -> Result<u64> {
+        let cutoff = timestamp(now - chrono::Duration::days(retention_days));
+        on_pool!(&self.pool, |pool| sqlx::query(
+            "DELETE FROM schedule_runs WHERE run_at < $1"
+        )
+        .bind(&cutoff)
+        .execute(pool)
+        .await
+        .map(|r| r.rows_affected()))
+        .context("prune schedule runs")
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct AgentScheduleRepository {
+    pool: DbPool,
+}
+
+impl AgentScheduleRepository {
+    pub fn new(pool: DbPool) -> Self {
+        Self { pool }
+    }
+
+    pub async fn list(&self, user_id: &str) -> Result<Vec<AgentSchedule>> {
+        let sql = format!(
+            "SELECT {AGENT_SCHEDULE_COLUMNS} FROM agent_schedules WHERE user_id = $1 ORDER BY name"
+        );
+        on_pool!(&self.pool, |pool| sqlx::query_as::<_, AgentSchedule>(&sql)
+            .bind(user_id)
+            .fetch_all(pool)
+            .await)
+        .context("list agent schedules")
+    }
+
+    pub async fn get(&self, user_id: &str, name: &str) -> Result<Option<AgentSchedule>> {
+        let sql = format!(
+            "SELECT {AGENT_SCHEDULE_COLUMNS} FROM agent_schedules WHERE user_id = $1 AND name = $2"
+        );
+        on_pool!(&self.pool, |pool| sqlx::query_as::<_, AgentSchedule>(&sql)
+            .bind(user_id)
+            .bind(name)
+            .fetch_optional(pool)
+            .await)
+        .context("get agent schedule")
+    }
+
+    pub async fn count(&self, user_id: &str) -> Result<i64> {
+        on_pool!(&self.pool, |pool| sqlx::query_scalar(
+            "SELECT COUNT(*) FROM agent_schedules WHERE user_id = $1"
+        )
+        .bind(user_id)
+        .fetch_one(pool)
+        .await)
+        .context("count agent schedules")
+    }
+
+    /// Create the schedule `name`, or replace its settings when it exists.
+    pub async fn upsert(
+        &self,
+        user_id: &str,
+        name: &str,
+        request: &SaveAgentScheduleRequest,
+        next_run_at: Option<DateTime<Utc>>,
+        now: DateTime<Utc>,
+    ) -> Result<AgentSchedule> {
+        let now = timestamp(now);
+        let next_run_at = next_run_at.map(timestamp);
+        let id = format!("asch_{}", uuid::Uuid::new_v4().simple());
+        let sql = format!(
+            r#"INSERT INTO agent_schedules
+                   (id, user_id, name, cron, workspace_path, prompt, template, harness,
+                    provider, model, timeout_secs, enabled, next_run_at, created_at, updated_at)
+               VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $14)
+               ON CONFLICT (user_id, name) DO UPDATE SET
+                   cron = excluded.cron,
+                   workspace_path = excluded.workspace_path,
+                   prompt = excluded.prompt,
+                   template = excluded.template,
+                   harness = excluded.harness,
+                   provider = excluded.provider,
+                   model = excluded.model,
+                   timeout_secs = excluded.timeout_secs,
+                   enabled = excluded.enabled,
+                   next_run_at = excluded.next_run_at,
+                   updated_at = excluded.updated_at
+               RETURNING {AGENT_SCHEDULE_COLUMNS}"#
+        );
+        on_pool!(&self.pool, |pool| sqlx::query_as::<_, AgentSchedule>(&sql)
+            .bind(&id)
+            .bind(user_id)
+            .bind(name)
+            .bind(&request.cron)
+            .bind(&request.workspace_path)
+            .bind(&request.prompt)
+            .bind(&request.template)
+            .bind(&request.harness)
+            .bind(&request.provider)
+            .bind(&request.model)
+            .bind(request.timeout_secs)
+            .bind(request.enabled)
+            .bind(&next_run_at)
+            .bind(&now)
+            .fetch_one(pool)
+            .await)
+        .context("upsert agent schedule")
+    }
+
+    pub async fn delete(&self, user_id: &str, name: &str) -> Result<bool> {
+        on_pool!(&self.pool, |pool| sqlx::query(
+            "DELETE FROM agent_schedules WHERE user_id = $1 AND name = $2"
+        )
+        .bind(user_id)
+        .bind(name)
+        .execute(pool)
+        .await
+        .map(|r| r.rows_affected() > 0))
+        .context("delete agent schedule")
+    }
+
+    /// Enabled schedules whose next run is at or before `now`.
+    pub async fn due(&self, now: DateTime<Utc>) -> Result<Vec<AgentSchedule>> {
+        let sql = format!(
+            "SELECT {AGENT_SCHEDULE_COLUMNS} FROM agent_schedules \
+             WHERE enabled = $1 AND next_run_at IS NOT NULL AND next_run_at <= $2 \
+             ORDER BY next_run_at"
+        );
+        on_pool!(&self.pool, |pool| sqlx::query_as::<_, AgentSchedule>(&sql)
+            .bind(true)
+            .bind(timestamp(now))
+            .fetch_all(pool)
+            .await)
+        .context("list due agent schedules")
+    }
+
+    /// Move a schedule from the run due at `due` on to its next run. False
+    /// when the schedule no longer has that run due, because another
+    /// replica claimed it or the schedule was changed meanwhile.
+    pub async fn claim(
+        &self,
+        id: &str,
+        due: &str,
+        next_run_at: Option<DateTime<Utc>>,
+    ) -> Result<bool> {
+        let next_run_at = next_run_at.map(timestamp);
+        on_pool!(&self.pool, |pool| sqlx::query(
+            "UPDATE agent_schedules SET next_run_at = $1 WHERE id = $2 AND next_run_at = $3"
+        )
+        .bind(&next_run_at)
+        .bind(id)
+        .bind(due)
+        .execute(pool)
+        .await
+        .map(|r| r.rows_affected() > 0))
+        .context("claim agent schedule run")
+    }
+
+    pub async fn mark_run(&self, id: &str, at: DateTime<Utc>) -> Result<()> {
+        let at = timestamp(at);
+        on_pool!(&self.pool, |pool| sqlx::query(
+            "UPDATE agent_schedules SET last_run_at = $1 WHERE id = $2"
+        )
+        .bind(&at)
+        .bind(id)
+        .execute(pool)
+        .await
+        .map(|_| ()))
+        .context("mark agent schedule run")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::Database;
+
+    fn report(id: &str, status: ScheduleRunStatus, started_at: &str) -> ScheduleRunReport {
+        ScheduleRunReport {
+            id: Some(id.to_string()),
+            status,
+            scheduled_for: None,
+            started_at: Some(started_at.to_string()),
+            ended_at: None,
+            exit_code: None,
+            log_path: None,
+            cost_usd: None,
+            error: None,
+            session_id: None,
+        }
+    }
+
+    async fn repo() -> ScheduleRunRepository {
+        let db = Database::in_memory().await.unwrap();
+        sqlx::query("INSERT INTO users (id, username, email, display_name) VALUES (?, ?, ?, ?)")
+            .bind("alice")
+            .bind("alice")
+            .bind("alice@example.com")
+            .bind("Alice")
+            .execute(db.pool())
+            .await
+            .unwrap();
+        ScheduleRunRepository::new(db.shared().clone())
+    }
+
+    #[tokio::test]
+    async fn test_run_history_and_streak() {
+        let repo = repo().await;
+        let now = Utc::now();
+
+        let started = repo
+            .upsert_run(
+                "alice",
+                "backup",
+                &report("r1", ScheduleRunStatus::Running, "2026-05-13T02:00:00.000Z"),
+                now,
+            )
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(started.status, ScheduleRunStatus::Running);
+
+        // Finishing the run keeps fields that are not reported again.
+        let mut done = report("r1", ScheduleRunStatus::Failed, "2026-05-13T02:00:00.000Z");
+        done.started_at = None;
+        done.exit_code = Some(1);
+        done.cost_usd = Some(0.12);
+        let finished = repo
+            .upsert_run("alice", "backup", &done, now)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(finished.status, ScheduleRunStatus::Failed);
+        assert_eq!(
+            finished.started_at.as_deref(),
+            Some("2026-05-13T02:00:00.000Z")
+        );
+        assert_eq!(finished.exit_code, Some(1));
+
+        // The ID is taken by another schedule.
+        assert!(
+            repo.upsert_run("alice", "cleanup", &done, now)
+                .await
+                .unwrap()
+                .is_none()
+        );
+
+        for (id, status, at) in [
+            (
+                "r0",
+                ScheduleRunStatus::Succeeded,
+                "2026-05-12T02:00:00.000Z",
+            ),
+            ("r2", ScheduleRunStatus::Failed, "2026-05-14T02:00:00.000Z"),
+        ] {
+            repo.upsert_run("alice", "backup", &report(id, status, at), now)
+                .await
+                .unwrap();
+        }
+        let due = chrono::DateTime::parse_from_rfc3339("2026-05-15T02:00:00Z")
+            .unwrap()
+            .with_timezone(&Utc);
+        assert_eq!(
+            repo.record_skipped("alice", "backup", &[due])
+                .await
+                .unwrap(),
+            1
+        );
+        assert_eq!(
+            repo.record_skipped("alice", "backup", &[due])
+                .await
+                .unwrap(),
+            0
+        );
+
+        assert_eq!(repo.failure_streak("alice", "backup").await.unwrap(), 2);
+
+        let (page, total) = repo
+            .list(
+                "alice",
+                "backup",
+                &ScheduleRunQuery {
+                    limit: Some(2),
+                    offset: Some(1),
+                    ..Default::default()
+                },
+            )
+            .await
+            .unwrap();
+        assert_eq!(total, 4);
+        assert_eq!(
+            page.iter().map(|r| r.id.as_str()).collect::<Vec<_>>(),
+            vec!["r2", "r1"]
+        );
+
+        let (skipped, total) = repo
+            .list(
+                "alice",
+                "backup",
+                &ScheduleRunQuery {
+                    status: Some(ScheduleRunStatus::Skipped),
+                    ..Default::default()
+                },
+            )
+            .await
+            .unwrap();
+        assert_eq!(total, 1);
+        assert_eq!(
+            skipped[0].scheduled_for.as_deref(),
+            Some("2026-05-15T02:00:00.000Z")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_definitions_and_heartbeat() {
+        let repo = repo().await;
+        let now = Utc::now();
+
+        assert!(repo.last_heartbeat().await.unwrap().is_none());
+        repo.heartbeat(now).await.unwrap();
+        repo.heartbeat(now).await.unwrap();
+        assert_eq!(repo.last_heartbeat().await.unwrap(), Some(timestamp(now)));
+
+        let schedules = vec![
+            ("backup".to_string(), "0 2 * * *".to_string()),
+            ("cleanup".to_string(), "*/30 * * * *".to_string()),
+        ];
+        repo.sync_definitions("alice", &schedules, now)
+            .await
+            .unwrap();
+        repo.sync_definitions("alice", &schedules[..1], now)
+            .await
+            .unwrap();
+        let defs = repo.definitions().await.unwrap();
+        assert_eq!(defs.len(), 1);
+        assert_eq!(defs[0].schedule, "0 2 * * *");
+    }
+}
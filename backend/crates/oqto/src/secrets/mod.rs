@@ -0,0 +1,379 @@
+//! Secrets agents need, kept out of workspaces.
+//!
+//! Users store API keys and credential files through `/api/secrets`, either
+//! for all their sessions or for one project (a workspace path). Values are
+//! sealed with the server key (`[secrets] key_file`) before they reach the
+//! database and are never returned by the API. When a session starts, the
+//! secrets in its scope are opened and injected: `env` secrets as
+//! environment variables, `file` secrets as files in a private directory
+//! outside the workspace that `OQTO_SECRETS_DIR` points at. A project's
+//! secret wins over a user-wide one with the same name.
+
+mod repository;
+mod sealed;
+
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use oqto_runner::protocol::SessionSecrets;
+use serde::{Deserialize, Serialize};
+use tracing::warn;
+
+pub use repository::{SecretRepository, SecretRow};
+pub use sealed::Sealer;
+
+/// Largest secret value accepted.
+pub const MAX_SECRET_BYTES: usize = 64 * 1024;
+
+/// Where the secret files of a container session are mounted.
+pub const CONTAINER_SECRETS_DIR: &str = "/run/oqto/secrets";
+
+/// `[secrets]` configuration section.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SecretsConfig {
+    /// Serve `/api/secrets` and inject secrets into sessions.
+    pub enabled: bool,
+    /// File holding the key secrets are sealed with, generated on first
+    /// start (default: `secrets.key` in the data directory). Keep it out of
+    /// backups of the database. With a shared database (`[db] url`) it is
+    /// never generated: every replica must be given the same key.
+    pub key_file: Option<PathBuf>,
+    /// The key itself, hex encoded, instead of `key_file`; supports
+    /// `env:VAR_NAME`.
+    pub key: Option<String>,
+}
+
+impl Default for SecretsConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            key_file: None,
+            key: None,
+        }
+    }
+}
+
+/// How a secret reaches a session.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SecretKind {
+    /// An environment variable named after the secret.
+    #[default]
+    Env,
+    /// A file named after the secret in `$OQTO_SECRETS_DIR`.
+    File,
+}
+
+impl SecretKind {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            SecretKind::Env => "env",
+            SecretKind::File => "file",
+        }
+    }
+
+    fn parse(kind: &str) -> Option<Self> {
+        match kind {
+            "env" => Some(SecretKind::Env),
+            "file" => Some(SecretKind::File),
+            _ => None,
+        }
+    }
+}
+
+/// A stored secret without its value.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct SecretInfo {
+    pub id: String,
+    pub kind: SecretKind,
+    pub name: String,
+    /// Project the secret is limited to; None for all of the user's sessions.
+    pub workspace_path: Option<String>,
+    pub created_at: String,
+    pub updated_at: String,
+}
+
+impl From<SecretRow> for SecretInfo {
+    fn from(row: SecretRow) -> Self {
+        Self {
+            id: row.id,
+            kind: SecretKind::parse(&row.kind).unwrap_or_default(),
+            name: row.name,
+            workspace_path: Some(row.workspace_path).filter(|p| !p.is_empty()),
+            created_at: row.created_at,
+            updated_at: row.updated_at,
+        }
+    }
+}
+
+/// Body of `POST /secrets`.
+#[derive(Debug, Clone, Deserialize)]
+pub struct CreateSecretRequest {
+    /// Variable name for `env` secrets, file name for `file` secrets.
+    pub name: String,
+    #[serde(default)]
+    pub kind: SecretKind,
+    pub value: String,
+    /// Limit the secret to sessions in this project.
+    #[serde(default)]
+    pub workspace_path: Option<String>,
+}
+
+/// Body of `PUT /secrets/{id}`.
+#[derive(Debug, Clone, Deserialize)]
+pub struct UpdateSecretRequest {
+    pub value: String,
+}
+
+/// Check that `name` can be injected as a secret of `kind`.
+pub fn validate_name(kind: SecretKind, name: &str) -> Result<(), String> {
+    match kind {
+        SecretKind::Env => {
+            let mut chars = name.chars();
+            let valid = chars
+                .next()
+                .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+                && chars.all(|c| c.is_ascii_alphanumeric() || c == '_');
+            if !valid {
+                return Err(format!("{name:?} is not a valid environment variable name"));
+            }
+            if name.starts_with("OQTO_") {
+                return Err("variables starting with OQTO_ are reserved".to_string());
+            }
+        }
+        SecretKind::File => {
+            let valid = !name.starts_with('.')
+                && name.len() <= 255
+                && name
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '_' | '-'));
+            if !valid {
+                return Err(format!("{name:?} is not a valid file name"));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Sealed storage of secrets and their injection into sessions.
+#[derive(Debug)]
+pub struct SecretStore {
+    repo: SecretRepository,
+    sealer: Sealer,
+    /// Host directory holding the secret files of container sessions.
+    container_files: PathBuf,
+}
+
+impl SecretStore {
+    pub fn new(repo: SecretRepository, sealer: Sealer, container_files: PathBuf) -> Self {
+        Self {
+            repo,
+            sealer,
+            container_files,
+        }
+    }
+
+    pub async fn list(&self, user_id: &str) -> Result<Vec<SecretInfo>> {
+        Ok(self
+            .repo
+            .list(user_id)
+            .await?
+            .into_iter()
+            .map(SecretInfo::from)
+            .collect())
+    }
+
+    pub async fn get(&self, user_id: &str, id: &str) -> Result<Option<SecretInfo>> {
+        Ok(self.repo.get(user_id, id).await?.map(SecretInfo::from))
+    }
+
+    /// Store a secret. `workspace_path` must already be resolved to the
+    /// project's canonical path.
+    pub async fn create(
+        &self,
+        user_id: &str,
+        workspace_path: Option<&str>,
+        kind: SecretKind,
+        name: &str,
+        value: &str,
+    ) -> Result<SecretInfo> {
+        let sealed = self.sealer.seal(value)?;
+        let row = self
+            .repo
+            .create(
+                user_id,
+                workspace_path.unwrap_or_default(),
+                kind.as_str(),
+                name,
+                &sealed,
+            )
+            .await?;
+        Ok(row.into())
+    }
+
+    /// Replace the value of a secret. False when the user has no such secret.
+    pub async fn update(&self, user_id: &str, id: &str, value: &str) -> Result<bool> {
+        let sealed = self.sealer.seal(value)?;
+        self.repo.update_value(user_id, id, &sealed).await
+    }
+
+    pub async fn delete(&self, user_id: &str, id: &str) -> Result<bool> {
+        self.repo.delete(user_id, id).await
+    }
+
+    /// The opened secrets of a user that apply to a session in
+    /// `workspace_path`. Secrets that cannot be opened are logged and left
+    /// out, so the session still starts.
+    pub async fn for_session(
+        &self,
+        user_id: &str,
+        workspace_path: &Path,
+    ) -> Result<SessionSecrets> {
+        let mut secrets = SessionSecrets::default();
+        for row in in_scope(self.repo.list(user_id).await?, workspace_path) {
+            let value = match self.sealer.open(&row.sealed_value) {
+                Ok(value) => value,
+                Err(e) => {
+                    warn!(secret_id = %row.id, "Skipping secret: {e:#}");
+                    continue;
+                }
+            };
+            match SecretKind::parse(&row.kind) {
+                Some(SecretKind::Env) => {
+                    secrets.env.insert(row.name, value);
+                }
+                Some(SecretKind::File) => {
+                    secrets.files.insert(row.name, value);
+                }
+                None => warn!(secret_id = %row.id, kind = %row.kind, "Skipping secret"),
+            }
+        }
+        Ok(secrets)
+    }
+
+    /// (Re)write the secret files of a container session and return the
+    /// host directory to mount at [`CONTAINER_SECRETS_DIR`]. The directory is
+    /// created even without files, since a resumed container still mounts
+    /// it. Only the server user can enter its parent; the files themselves
+    /// are readable by all so the container's user can read them whatever
+    /// uid it maps to.
+    pub fn write_container_files(
+        &self,
+        session_id: &str,
+        files: &BTreeMap<String, String>,
+    ) -> Result<PathBuf> {
+        let dir = self.container_files.join(session_id);
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).with_context(|| format!("creating {}", dir.display()))?;
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            std::fs::set_permissions(
+                &self.container_files,
+                std::fs::Permissions::from_mode(0o700),
+            )?;
+            std::fs::set_permissions(&dir, std::fs::Permissions::from_mode(0o755))?;
+        }
+        for (name, content) in files {
+            let path = dir.join(name);
+            std::fs::write(&path, content)
+                .with_context(|| format!("writing {}", path.display()))?;
+            #[cfg(unix)]
+            {
+                use std::os::unix::fs::PermissionsExt;
+                std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o644))?;
+            }
+        }
+        Ok(dir)
+    }
+
+    /// Remove the secret files of a container session.
+    pub fn remove_container_files(&self, session_id: &str) {
+        let _ = std::fs::remove_dir_all(self.container_files.join(session_id));
+    }
+}
+
+/// The secrets applying to a session in `workspace_path`, ordered so that
+/// those of nested projects come after (and override) broader ones.
+fn in_scope(rows: Vec<SecretRow>, workspace_path: &Path) -> Vec<SecretRow> {
+    let mut rows: Vec<SecretRow> = rows
+        .into_iter()
+        .filter(|row| {
+            row.workspace_path.is_empty() || workspace_path.starts_with(&row.workspace_path)
+        })
+        .collect();
+    rows.sort_by_key(|row| Path::new(&row.workspace_path).components().count());
+    rows
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::Database;
+
+    #[test]
+    fn test_validate_name() {
+        assert!(validate_name(SecretKind::Env, "OPENAI_API_KEY").is_ok());
+        assert!(validate_name(SecretKind::Env, "1PASSWORD").is_err());
+        assert!(validate_name(SecretKind::Env, "OQTO_SECRETS_DIR").is_err());
+        assert!(validate_name(SecretKind::File, "gcp-service-account.json").is_ok());
+        assert!(validate_name(SecretKind::File, "../id_rsa").is_err());
+        assert!(validate_name(SecretKind::File, ".netrc").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_for_session_scopes() {
+        let db = Database::in_memory().await.unwrap();
+        sqlx::query("INSERT INTO users (id, username, email, display_name) VALUES (?, ?, ?, ?)")
+            .bind("alice")
+            .bind("alice")
+            .bind("alice@example.com")
+            .bind("alice")
+            .execute(db.pool())
+            .await
+            .unwrap();
+        let files = tempfile::tempdir().unwrap();
+        let store = SecretStore::new(
+            SecretRepository::new(db.shared().clone()),
+            Sealer::from_bytes([1; 32]),
+            files.path().join("session-secrets"),
+        );
+        store
+            .create("alice", None, SecretKind::Env, "API_KEY", "user-wide")
+            .await
+            .unwrap();
+        store
+            .create("alice", Some("/w/app"), SecretKind::Env, "API_KEY", "app")
+            .await
+            .unwrap();
+        let file = store
+            .create("alice", Some("/w/app"), SecretKind::File, "sa.json", "{}")
+            .await
+            .unwrap();
+        assert_eq!(file.workspace_path.as_deref(), Some("/w/app"));
+
+        let app = store
+            .for_session("alice", Path::new("/w/app/sub"))
+            .await
+            .unwrap();
+        assert_eq!(app.env["API_KEY"], "app");
+        assert_eq!(app.files["sa.json"], "{}");
+        let dir = store.write_container_files("ses-1", &app.files).unwrap();
+        assert_eq!(std::fs::read_to_string(dir.join("sa.json")).unwrap(), "{}");
+        store.remove_container_files("ses-1");
+        assert!(!dir.exists());
+        let other = store
+            .for_session("alice", Path::new("/w/application"))
+            .await
+            .unwrap();
+        assert_eq!(other.env["API_KEY"], "user-wide");
+        assert!(other.files.is_empty());
+
+        assert!(store.update("alice", &file.id, "{\"k\":1}").await.unwrap());
+        assert!(!store.delete("bob", &file.id).await.unwrap());
+        assert!(store.delete("alice", &file.id).await.unwrap());
+        assert_eq!(store.list("alice").await.unwrap().len(), 2);
+    }
+}
@@ -0,0 +1,122 @@
+use anyhow::{Context, Result};
+use sqlx::FromRow;
+
+use crate::db::{self, DbPool, on_pool};
+
+/// A stored secret, its value still sealed.
+#[derive(Debug, Clone, FromRow)]
+pub struct SecretRow {
+    pub id: String,
+    pub user_id: String,
+    /// Project the secret is limited to, '' for all of the user's sessions.
+    pub workspace_path: String,
+    pub kind: String,
+    pub name: String,
+    pub sealed_value: String,
+    pub created_at: String,
+    pub updated_at: String,
+}
+
+const COLUMNS: &str =
+    "id, user_id, workspace_path, kind, name, sealed_value, created_at, updated_at";
+
+#[derive(Debug, Clone)]
+pub struct SecretRepository {
+    pool: DbPool,
+}
+
+impl SecretRepository {
+    pub fn new(pool: DbPool) -> Self {
+        Self { pool }
+    }
+
+    pub async fn create(
+        &self,
+        user_id: &str,
+        workspace_path: &str,
+        kind: &str,
+        name: &str,
+        sealed_value: &str,
+    ) -> Result<SecretRow> {
+        let id = format!("sec_{}", uuid::Uuid::new_v4().simple());
+        let now = db::now();
+        on_pool!(&self.pool, |pool| sqlx::query(
+            r#"INSERT INTO secrets
+                   (id, user_id, workspace_path, kind, name, sealed_value, created_at, updated_at)
+               VALUES ($1, $2, $3, $4, $5, $6, $7, $7)"#
+        )
+        .bind(&id)
+        .bind(user_id)
+        .bind(workspace_path)
+        .bind(kind)
+        .bind(name)
+        .bind(sealed_value)
+        .bind(&now)
+        .execute(pool)
+        .await)
+        .context("create secret")?;
+        Ok(SecretRow {
+            id,
+            user_id: user_id.to_string(),
+            workspace_path: workspace_path.to_string(),
+            kind: kind.to_string(),
+            name: name.to_string(),
+            sealed_value: sealed_value.to_string(),
+            created_at: now.clone(),
+            updated_at: now,
+        })
+    }
+
+    pub async fn get(&self, user_id: &str, id: &str) -> Result<Option<SecretRow>> {
+        let sql = format!("SELECT {COLUMNS} FROM secrets WHERE id = $1 AND user_id = $2");
+        on_pool!(&self.pool, |pool| sqlx::query_as::<_, SecretRow>(&sql)
+            .bind(id)
+            .bind(user_id)
+            .fetch_optional(pool)
+            .await)
+        .context("get secret")
+    }
+
+    /// A user's secrets, user-wide ones first.
+    pub async fn list(&self, user_id: &str) -> Result<Vec<SecretRow>> {
+        let sql = format!(
+            "SELECT {COLUMNS} FROM secrets WHERE user_id = $1 ORDER BY workspace_path, kind, name"
+        );
+        on_pool!(&self.pool, |pool| sqlx::query_as::<_, SecretRow>(&sql)
+            .bind(user_id)
+            .fetch_all(pool)
+            .await)
+        .context("list secrets")
+    }
+
+    /// Replace the value of a secret. False when the user has no such secret.
+    pub async fn update_value(&self, user_id: &str, id: &str, sealed_value: &str) -> Result<bool> {
+        let now = db::now();
+        let updated = on_pool!(&self.pool, |pool| sqlx::query(
+            r#"UPDATE secrets SET sealed_value = $1, updated_at = $2
+               WHERE id = $3 AND user_id = $4"#
+        )
+        .bind(sealed_value)
+        .bind(&now)
+        .bind(id)
+        .bind(user_id)
+        .execute(pool)
+        .await
+        .map(|r| r.rows_affected()))
+        .context("update secret")?;
+        Ok(updated > 0)
+    }
+
+    pub async fn delete(&self, user_id: &str, id: &str) -> Result<bool> {
+        let deleted = on_pool!(&self.pool, |pool| sqlx::query(
+            "DELETE FROM secrets WHERE id = $1 AND user_id = $2"
+        )
+        .bind(id)
+        .bind(user_id)
+        .execute(pool)
+        .await
+        .map(|r| r.rows_affected()))
+        .context("delete secret")?;
+        Ok(deleted > 0)
+    }
+}
@@ -0,0 +1,119 @@
+use std::path::Path;
+
+use anyhow::{Context, Result, anyhow};
+use base64::Engine;
+use crypto_box::aead::OsRng;
+use crypto_box::{PublicKey, SecretKey};
+
+/// Seals secret values for storage and opens them at session start, with
+/// libsodium sealed boxes (X25519, XSalsa20-Poly1305) under the server key.
+pub struct Sealer {
+    secret: SecretKey,
+    public: PublicKey,
+}
+
+impl std::fmt::Debug for Sealer {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Sealer").finish_non_exhaustive()
+    }
+}
+
+impl Sealer {
+    pub fn from_bytes(bytes: [u8; 32]) -> Self {
+        let secret = SecretKey::from(bytes);
+        let public = secret.public_key();
+        Self { secret, public }
+    }
+
+    /// A hex encoded 32-byte key.
+    pub fn from_hex(key: &str) -> Result<Self> {
+        let bytes: [u8; 32] = hex::decode(key.trim())
+            .ok()
+            .and_then(|bytes| bytes.try_into().ok())
+            .context("not a hex encoded 32-byte key")?;
+        Ok(Self::from_bytes(bytes))
+    }
+
+    /// Load the key stored (hex encoded) at `path`, which must exist.
+    pub fn load(path: &Path) -> Result<Self> {
+        let key =
+            std::fs::read_to_string(path).with_context(|| format!("reading {}", path.display()))?;
+        Self::from_hex(&key).with_context(|| format!("{} does not hold a key", path.display()))
+    }
+
+    /// Load the key stored (hex encoded) at `path`, generating it on first
+    /// use. Losing the file makes every stored secret unreadable.
+    pub fn load_or_create(path: &Path) -> Result<Self> {
+        match std::fs::metadata(path) {
+            Ok(_) => return Self::load(path),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => return Err(e).with_context(|| format!("reading {}", path.display())),
+        }
+
+        let bytes = rand::random::<[u8; 32]>();
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)
+                .with_context(|| format!("creating {}", parent.display()))?;
+        }
+        let mut options = std::fs::OpenOptions::new();
+        options.write(true).create_new(true);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::OpenOptionsExt;
+            options.mode(0o600);
+        }
+        let mut file = options
+            .open(path)
+            .with_context(|| format!("creating {}", path.display()))?;
+        std::io::Write::write_all(&mut file, hex::encode(bytes).as_bytes())
+            .with_context(|| format!("writing {}", path.display()))?;
+        Ok(Self::from_bytes(bytes))
+    }
+
+    /// Seal `value`, base64 encoded for storage.
+    pub fn seal(&self, value: &str) -> Result<String> {
+        let sealed = self
+            .public
+            .seal(&mut OsRng, value.as_bytes())
+            .map_err(|_| anyhow!("sealing secret failed"))?;
+        Ok(base64::engine::general_purpose::STANDARD.encode(sealed))
+    }
+
+    /// Open a value sealed by [`Sealer::seal`].
+    pub fn open(&self, sealed: &str) -> Result<String> {
+        let sealed = base64::engine::general_purpose::STANDARD
+            .decode(sealed)
+            .context("decoding sealed secret")?;
+        let value = self
+            .secret
+            .unseal(&sealed)
+            .map_err(|_| anyhow!("secret cannot be opened with the server key"))?;
+        String::from_utf8(value).context("secret is not UTF-8")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_seal_roundtrip() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("secrets.key");
+        let sealer = Sealer::load_or_create(&path).unwrap();
+        let sealed = sealer.seal("sk-live-123").unwrap();
+        assert!(!sealed.contains("sk-live-123"));
+
+        let reloaded = Sealer::load_or_create(&path).unwrap();
+        assert_eq!(reloaded.open(&sealed).unwrap(), "sk-live-123");
+        assert!(Sealer::from_bytes([7; 32]).open(&sealed).is_err());
+
+        let key = std::fs::read_to_string(&path).unwrap();
+        assert_eq!(
+            Sealer::from_hex(&key).unwrap().open(&sealed).unwrap(),
+            "sk-live-123"
+        );
+        assert!(Sealer::from_hex("abcd").is_err());
+        assert!(Sealer::load(&dir.path().join("missing.key")).is_err());
+    }
+}
@@ -0,0 +1,200 @@
+//! Egress rules for container sessions.
+//!
+//! Local sessions have their egress policy enforced by the runner's relay in
+//! sandbox proxy mode. Containers bring their own network namespace, so the
+//! backend attaches a relay and nftables rules to each container before its
+//! entrypoint runs (see [`oqto_sandbox::egress::attach_container`]) and
+//! announces the connections it blocks as [`BlockedEvent`]s. The rules come
+//! from sandbox.toml with the sandbox profile the session selected nested in.
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use anyhow::{Context, Result};
+use log::{info, warn};
+use oqto_sandbox::{BlockedConnection, EgressGuard, EgressPolicy, NetworkMode, SandboxConfig};
+use tokio::io::{AsyncBufReadExt, BufReader};
+use tokio::sync::broadcast;
+
+/// Blocked connections buffered for slow subscribers.
+const BLOCKED_BUFFER_SIZE: usize = 256;
+
+/// A connection of a session the egress policy blocked.
+#[derive(Debug, Clone)]
+pub struct BlockedEvent {
+    pub session_id: String,
+    pub user_id: String,
+    pub connection: BlockedConnection,
+}
+
+/// What a session's container is allowed to reach.
+#[derive(Debug, Clone, PartialEq)]
+pub enum EgressRules {
+    /// Connections are checked against the policy by a relay.
+    Policy(EgressPolicy),
+    /// Nothing beyond replies to connections made to the container.
+    Isolated,
+}
+
+/// The sandbox config of container sessions and the rules attached to their
+/// containers.
+#[derive(Clone)]
+pub struct ContainerEgress {
+    /// sandbox.toml, or why it could not be loaded.
+    sandbox: Arc<std::result::Result<SandboxConfig, String>>,
+    /// Attached rules by session; dropping a guard removes them.
+    guards: Arc<Mutex<HashMap<String, EgressGuard>>>,
+    blocked: broadcast::Sender<BlockedEvent>,
+}
+
+impl ContainerEgress {
+    pub fn new(sandbox: SandboxConfig) -> Self {
+        Self::with_sandbox(Ok(sandbox))
+    }
+
+    /// Egress for a sandbox.toml that cannot be loaded: every container
+    /// session fails to start with `error` instead of running without rules.
+    pub fn unavailable(error: &anyhow::Error) -> Self {
+        Self::with_sandbox(Err(format!("{error:#}")))
+    }
+
+    fn with_sandbox(sandbox: std::result::Result<SandboxConfig, String>) -> Self {
+        Self {
+            sandbox: Arc::new(sandbox),
+            guards: Arc::default(),
+            blocked: broadcast::channel(BLOCKED_BUFFER_SIZE).0,
+        }
+    }
+
+    /// Subscribe to blocked connections of all sessions.
+    pub fn subscribe(&self) -> broadcast::Receiver<BlockedEvent> {
+        self.blocked.subscribe()
+    }
+
+    /// The rules for a session with the sandbox `profile` it selected, if
+    /// any. None when its sandbox leaves the network open. Fails when the
+    /// sandbox config cannot be loaded or the profile is unknown.
+    pub fn rules_for(&self, profile: Option<&str>) -> Result<Option<EgressRules>> {
+        let sandbox = match self.sandbox.as_ref() {
+            Ok(sandbox) => sandbox,
+            Err(e) => anyhow::bail!("the sandbox config cannot be loaded: {e}"),
+        };
+        let sandbox = match profile {
+            Some(profile) => sandbox.with_session_profile(profile)?,
+            None => sandbox.clone(),
+        };
+        if !sandbox.enabled {
+            return Ok(None);
+        }
+        let network = sandbox.network.clone().unwrap_or_default();
+        if sandbox.isolate_network || network.mode == NetworkMode::Isolated {
+            return Ok(Some(EgressRules::Isolated));
+        }
+        let policy = EgressPolicy::from_network(&network);
+        Ok((!policy.is_empty()).then_some(EgressRules::Policy(policy)))
+    }
+
+    /// Enforce `rules` on the container of a session, given the host pid of
+    /// its main process. Replaces rules attached to an earlier container of
+    /// the session.
+    pub async fn attach(
+        &self,
+        session_id: &str,
+        user_id: &str,
+        pid: u32,
+        rules: &EgressRules,
+    ) -> Result<()> {
+        let rules = rules.clone();
+        let mut guard = tokio::task::spawn_blocking(move || match rules {
+            EgressRules::Policy(policy) => oqto_sandbox::egress::attach_container(pid, &policy),
+            EgressRules::Isolated => oqto_sandbox::egress::isolate_container(pid),
+        })
+        .await
+        .context("attaching egress rules")??;
+        if let Some(stdout) = guard.take_blocked() {
+            let stdout = tokio::process::ChildStdout::from_std(stdout)
+                .context("reading egress relay reports")?;
+            tokio::spawn(forward_blocked(
+                stdout,
+                self.blocked.clone(),
+                session_id.to_string(),
+                user_id.to_string(),
+            ));
+        }
+        info!("Egress rules attached to session {session_id} (pid {pid})");
+        self.guards
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .insert(session_id.to_string(), guard);
+        Ok(())
+    }
+
+    /// Remove the rules of a session's container, if any.
+    pub fn release(&self, session_id: &str) {
+        let guard = self
+            .guards
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .remove(session_id);
+        drop(guard);
+    }
+}
+
+/// Announce the JSON lines of a relay's reports until it exits.
+async fn forward_blocked(
+    stdout: tokio::process::ChildStdout,
+    blocked: broadcast::Sender<BlockedEvent>,
+    session_id: String,
+    user_id: String,
+) {
+    let mut lines = BufReader::new(stdout).lines();
+    while let Ok(Some(line)) = lines.next_line().await {
+        match serde_json::from_str::<BlockedConnection>(&line) {
+            Ok(connection) => {
+                let _ = blocked.send(BlockedEvent {
+                    session_id: session_id.clone(),
+                    user_id: user_id.clone(),
+                    connection,
+                });
+            }
+            Err(e) => warn!("Unreadable egress report for session {session_id}: {e}"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use oqto_sandbox::NetworkConfig;
+
+    #[test]
+    fn rules_follow_the_session_profile() {
+        let sandbox = SandboxConfig {
+            enabled: true,
+            network: Some(NetworkConfig {
+                deny_ports: vec![25],
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        let egress = ContainerEgress::new(sandbox);
+        let policy = EgressRules::Policy(EgressPolicy {
+            deny_ports: vec![25],
+            ..Default::default()
+        });
+        assert_eq!(egress.rules_for(None).unwrap(), Some(policy.clone()));
+        assert_eq!(egress.rules_for(Some("full")).unwrap(), Some(policy));
+        assert_eq!(
+            egress.rules_for(Some("net-off")).unwrap(),
+            Some(EgressRules::Isolated)
+        );
+        assert!(egress.rules_for(Some("no-such-profile")).is_err());
+
+        let open = ContainerEgress::new(SandboxConfig::default());
+        assert_eq!(open.rules_for(None).unwrap(), None);
+
+        let broken = ContainerEgress::unavailable(&anyhow::anyhow!("bad toml"));
+        let err = broken.rules_for(None).unwrap_err();
+        assert!(err.to_string().contains("bad toml"), "{err}");
+    }
+}
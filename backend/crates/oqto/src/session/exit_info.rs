@@ -0,0 +1,253 @@
+//! Why a session stopped: exit data of its container or processes, classified
+//! into common failures with a hint for users.
+
+use serde::{Deserialize, Serialize};
+use ts_rs::TS;
+
+/// Kind of failure behind a session exit.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, TS)]
+#[serde(rename_all = "snake_case")]
+#[ts(export, export_to = "../../../../frontend/src/generated/")]
+pub enum ExitCategory {
+    /// No API key was configured for the model provider.
+    MissingApiKey,
+    /// Killed by the kernel for exceeding its memory limit.
+    OomKilled,
+    /// The harness or a service binary could not be found.
+    BinaryNotFound,
+    /// Killed by a signal.
+    Killed,
+    /// Exited with a non-zero code.
+    Crashed,
+    /// Exited with code 0.
+    Exited,
+    /// Failed without exit data; see the hint.
+    Error,
+}
+
+impl ExitCategory {
+    /// Failures that restarting does not fix.
+    pub fn is_permanent(&self) -> bool {
+        matches!(self, Self::MissingApiKey | Self::BinaryNotFound)
+    }
+}
+
+/// Structured exit diagnostics of a session.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "../../../../frontend/src/generated/")]
+pub struct ExitInfo {
+    /// Exit code, if the process exited on its own.
+    pub exit_code: Option<i32>,
+    /// Signal that killed the process, if any.
+    pub signal: Option<i32>,
+    /// Classified failure.
+    pub category: ExitCategory,
+    /// Human-readable explanation, e.g. "OOM killed (2GB limit)".
+    pub hint: String,
+    /// Service that exited, for local sessions (e.g. "fileserver").
+    pub service: Option<String>,
+}
+
+/// What is known about an exit, for [`ExitInfo::classify`].
+#[derive(Debug, Clone, Default)]
+pub struct ExitEvidence<'a> {
+    pub exit_code: Option<i32>,
+    pub signal: Option<i32>,
+    pub oom_killed: bool,
+    /// Memory limit in bytes.
+    pub memory_limit: Option<u64>,
+    /// Error message and output tail to look for known failures in.
+    pub output: &'a str,
+    pub service: Option<&'a str>,
+}
+
+impl ExitInfo {
+    /// Classify an exit.
+    pub fn classify(evidence: ExitEvidence<'_>) -> Self {
+        let output = evidence.output.to_ascii_lowercase();
+        // Runtimes report a process killed by a signal as 128 + signal.
+        let signal = evidence.signal.or_else(|| {
+            evidence
+                .exit_code
+                .filter(|code| (129..160).contains(code))
+                .map(|code| code - 128)
+        });
+
+        let (category, hint) = if evidence.oom_killed
+            || output.contains("out of memory")
+            || output.contains("oom-kill")
+        {
+            let hint = match evidence.memory_limit {
+                Some(limit) => format!("OOM killed ({} limit)", format_bytes(limit)),
+                None => "OOM killed".to_string(),
+            };
+            (ExitCategory::OomKilled, hint)
+        } else if evidence.exit_code == Some(127) || mentions_missing_binary(&output) {
+            (
+                ExitCategory::BinaryNotFound,
+                "Binary not found; check that the harness is installed and on PATH".to_string(),
+            )
+        } else if mentions_missing_api_key(&output) {
+            (
+                ExitCategory::MissingApiKey,
+                "Missing API key; configure a key for the model provider".to_string(),
+            )
+        } else if let Some(signal) = signal {
+            (
+                ExitCategory::Killed,
+                format!("Killed by {}", signal_name(signal)),
+            )
+        } else {
+            match evidence.exit_code {
+                Some(0) => (ExitCategory::Exited, "Exited normally".to_string()),
+                Some(code) => (ExitCategory::Crashed, format!("Exited with code {code}")),
+                None => (ExitCategory::Error, first_line(evidence.output)),
+            }
+        };
+
+        Self {
+            exit_code: evidence.exit_code,
+            signal,
+            category,
+            hint,
+            service: evidence.service.map(str::to_string),
+        }
+    }
+
+    /// Classify a failure known only by its error message.
+    pub fn from_error(message: &str) -> Self {
+        Self::classify(ExitEvidence {
+            output: message,
+            ..Default::default()
+        })
+    }
+}
+
+fn mentions_missing_binary(output: &str) -> bool {
+    [
+        "command not found",
+        "executable file not found",
+        "binary not found",
+        "no such file or directory (os error 2)",
+    ]
+    .iter()
+    .any(|needle| output.contains(needle))
+}
+
+fn mentions_missing_api_key(output: &str) -> bool {
+    let about_key = ["api key", "api_key", "apikey", "x-api-key"]
+        .iter()
+        .any(|needle| output.contains(needle));
+    let missing = [
+        "missing",
+        "not set",
+        "not configured",
+        "no api key",
+        "required",
+        "invalid",
+        "unauthorized",
+    ]
+    .iter()
+    .any(|needle| output.contains(needle));
+    about_key && missing
+}
+
+fn signal_name(signal: i32) -> String {
+    match signal {
+        6 => "SIGABRT (signal 6)".to_string(),
+        9 => "SIGKILL (signal 9)".to_string(),
+        11 => "SIGSEGV (signal 11)".to_string(),
+        15 => "SIGTERM (signal 15)".to_string(),
+        _ => format!("signal {signal}"),
+    }
+}
+
+/// "2GB", "1.5GB" or "512MB".
+fn format_bytes(bytes: u64) -> String {
+    const MB: u64 = 1024 * 1024;
+    const GB: u64 = 1024 * MB;
+    if bytes >= GB {
+        if bytes % GB == 0 {
+            format!("{}GB", bytes / GB)
+        } else {
+            format!("{:.1}GB", bytes as f64 / GB as f64)
+        }
+    } else {
+        format!("{}MB", bytes / MB)
+    }
+}
+
+fn first_line(message: &str) -> String {
+    let line = message.lines().next().unwrap_or_default().trim();
+    if line.is_empty() {
+        return "Stopped unexpectedly".to_string();
+    }
+    line.chars().take(200).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_classify_oom() {
+        let info = ExitInfo::classify(ExitEvidence {
+            exit_code: Some(137),
+            oom_killed: true,
+            memory_limit: Some(2 * 1024 * 1024 * 1024),
+            ..Default::default()
+        });
+        assert_eq!(info.category, ExitCategory::OomKilled);
+        assert_eq!(info.hint, "OOM killed (2GB limit)");
+        assert_eq!(info.signal, Some(9));
+
+        // Without the OOM flag, 137 is just a SIGKILL.
+        let info = ExitInfo::classify(ExitEvidence {
+            exit_code: Some(137),
+            ..Default::default()
+        });
+        assert_eq!(info.category, ExitCategory::Killed);
+        assert_eq!(info.hint, "Killed by SIGKILL (signal 9)");
+    }
+
+    #[test]
+    fn test_classify_from_output() {
+        let info = ExitInfo::classify(ExitEvidence {
+            exit_code: Some(1),
+            output: "Error: No API key found for anthropic. Set ANTHROPIC_API_KEY.",
+            ..Default::default()
+        });
+        assert_eq!(info.category, ExitCategory::MissingApiKey);
+        assert!(info.category.is_permanent());
+
+        let info =
+            ExitInfo::from_error("failed to spawn pi: No such file or directory (os error 2)");
+        assert_eq!(info.category, ExitCategory::BinaryNotFound);
+
+        let info = ExitInfo::classify(ExitEvidence {
+            exit_code: Some(127),
+            ..Default::default()
+        });
+        assert_eq!(info.category, ExitCategory::BinaryNotFound);
+    }
+
+    #[test]
+    fn test_classify_plain_exits() {
+        let info = ExitInfo::classify(ExitEvidence {
+            exit_code: Some(2),
+            service: Some("fileserver"),
+            ..Default::default()
+        });
+        assert_eq!(info.category, ExitCategory::Crashed);
+        assert_eq!(info.hint, "Exited with code 2");
+        assert_eq!(info.service.as_deref(), Some("fileserver"));
+
+        let info = ExitInfo::from_error("services not ready after restart: timeout\ndetails");
+        assert_eq!(info.category, ExitCategory::Error);
+        assert_eq!(info.hint, "services not ready after restart: timeout");
+        assert!(!info.category.is_permanent());
+
+        assert_eq!(format_bytes(1536 * 1024 * 1024), "1.5GB");
+        assert_eq!(format_bytes(512 * 1024 * 1024), "512MB");
+    }
+}
@@ -0,0 +1,151 @@
+//! Container images built from a Dockerfile in a session's workspace.
+//!
+//! Images are tagged `oqto-build/<user>/<name>:<timestamp>` so a build can
+//! never replace the default session image or another user's build, and
+//! carry `oqto.user` and `oqto.session` labels. One build runs per session
+//! at a time.
+
+use std::collections::HashSet;
+use std::path::{Component, Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use chrono::Utc;
+
+/// Repository prefix of built images.
+pub const IMAGE_PREFIX: &str = "oqto-build";
+
+/// Tag of a new image of `user_id`, named after `name`.
+pub fn image_tag(user_id: &str, name: &str) -> String {
+    format!(
+        "{IMAGE_PREFIX}/{}/{}:{}",
+        tag_component(user_id, "user"),
+        tag_component(name, "workspace"),
+        Utc::now().format("%Y%m%d%H%M%S")
+    )
+}
+
+/// `raw` as an image path component: lowercase letters, digits, `.`, `_`
+/// and `-`, starting and ending with a letter or digit.
+fn tag_component(raw: &str, fallback: &str) -> String {
+    let mapped: String = raw
+        .to_lowercase()
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || matches!(c, '.' | '_' | '-') {
+                c
+            } else {
+                '-'
+            }
+        })
+        .collect();
+    let trimmed = mapped.trim_matches(|c: char| !c.is_ascii_alphanumeric());
+    if trimmed.is_empty() {
+        fallback.to_string()
+    } else {
+        trimmed.chars().take(64).collect()
+    }
+}
+
+/// The Dockerfile at `relative` inside `workspace`. Returns a message for
+/// the caller when it is missing or outside the workspace.
+pub fn resolve_dockerfile(workspace: &Path, relative: &str) -> Result<PathBuf, String> {
+    let rel = Path::new(relative.trim());
+    if rel.as_os_str().is_empty()
+        || rel
+            .components()
+            .any(|c| !matches!(c, Component::Normal(_) | Component::CurDir))
+    {
+        return Err(format!("invalid Dockerfile path '{relative}'"));
+    }
+    let workspace = workspace
+        .canonicalize()
+        .map_err(|e| format!("workspace is not accessible: {e}"))?;
+    let path = workspace
+        .join(rel)
+        .canonicalize()
+        .map_err(|_| format!("no Dockerfile at '{relative}' in the workspace"))?;
+    // Symlinks must not lead out of the workspace.
+    if !path.starts_with(&workspace) || !path.is_file() {
+        return Err(format!("no Dockerfile at '{relative}' in the workspace"));
+    }
+    Ok(path)
+}
+
+/// Sessions with a build running.
+#[derive(Debug, Default)]
+pub struct ImageBuilds {
+    running: Arc<Mutex<HashSet<String>>>,
+}
+
+impl ImageBuilds {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Mark a build of `session_id` as running until the guard is dropped.
+    /// None while another one runs.
+    pub fn start(&self, session_id: &str) -> Option<ImageBuildGuard> {
+        let mut running = self.running.lock().unwrap_or_else(|e| e.into_inner());
+        running
+            .insert(session_id.to_string())
+            .then(|| ImageBuildGuard {
+                running: Arc::clone(&self.running),
+                session_id: session_id.to_string(),
+            })
+    }
+}
+
+/// A running build; see [`ImageBuilds::start`].
+#[derive(Debug)]
+pub struct ImageBuildGuard {
+    running: Arc<Mutex<HashSet<String>>>,
+    session_id: String,
+}
+
+impl Drop for ImageBuildGuard {
+    fn drop(&mut self) {
+        self.running
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .remove(&self.session_id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tags_stay_in_the_users_namespace() {
+        let tag = image_tag("Alice@Example", "My App/../..");
+        let (repo, _) = tag.rsplit_once(':').unwrap();
+        assert_eq!(repo, "oqto-build/alice-example/my-app");
+        assert!(image_tag("", "..").starts_with("oqto-build/user/workspace:"));
+
+        let builds = ImageBuilds::new();
+        let guard = builds.start("ses_1").unwrap();
+        assert!(builds.start("ses_1").is_none());
+        drop(guard);
+        assert!(builds.start("ses_1").is_some());
+    }
+
+    #[test]
+    fn dockerfiles_must_be_inside_the_workspace() {
+        let temp = tempfile::tempdir().unwrap();
+        let workspace = temp.path().join("ws");
+        std::fs::create_dir_all(workspace.join("docker")).unwrap();
+        std::fs::write(workspace.join("Dockerfile"), "FROM scratch").unwrap();
+        std::fs::write(temp.path().join("Dockerfile"), "FROM scratch").unwrap();
+        std::os::unix::fs::symlink(
+            temp.path().join("Dockerfile"),
+            workspace.join("docker/Dockerfile"),
+        )
+        .unwrap();
+
+        assert!(resolve_dockerfile(&workspace, "Dockerfile").is_ok());
+        assert!(resolve_dockerfile(&workspace, "../Dockerfile").is_err());
+        assert!(resolve_dockerfile(&workspace, "/etc/passwd").is_err());
+        assert!(resolve_dockerfile(&workspace, "docker/Dockerfile").is_err());
+        assert!(resolve_dockerfile(&workspace, "Containerfile").is_err());
+    }
+}
@@ -3,19 +3,33 @@
 //! Handles the lifecycle of container sessions including creation,
 //! monitoring, and cleanup.
 
+mod egress;
+mod exit_info;
+pub mod image_builds;
 mod models;
+mod port_conflict;
 mod repository;
+pub mod resources;
 mod service;
 mod workspace_locations;
 
+pub use egress::{BlockedEvent, ContainerEgress};
+pub use exit_info::{ExitCategory, ExitEvidence, ExitInfo};
+pub use image_builds::ImageBuilds;
 #[allow(unused_imports)]
 pub use models::SessionStatus;
 #[allow(unused_imports)]
-pub use models::{CreateSessionRequest, RuntimeMode, Session, SessionResponse, SessionUrls};
+pub use models::{
+    CloneSessionRequest, CloneWorkspace, CreateSessionRequest, HibernatedAgent, Hibernation,
+    IdleAction, RuntimeMode, Session, SessionLifecycle, SessionLifecycleEvent, SessionMount,
+    SessionResponse, SessionSetup, SessionUrls, UserResourceLimits,
+};
+pub use port_conflict::{PortConflictEvent, PortHolder};
 pub use repository::SessionRepository;
+pub use resources::{ResourceTelemetryConfig, SessionResourceMonitor};
 #[allow(unused_imports)]
 pub use service::{
-    BrowserAction, ContainerStatsReport, SessionContainerStats, SessionService,
-    SessionServiceConfig,
+    BrowserAction, ContainerStatsReport, MAX_SETUP_ENV_VARS, SessionContainerStats, SessionLimits,
+    SessionService, SessionServiceConfig, validate_setup_env, validate_setup_mounts,
 };
 pub use workspace_locations::WorkspaceLocationInput;
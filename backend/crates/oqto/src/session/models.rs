@@ -4,6 +4,10 @@ use serde::{Deserialize, Serialize};
 use sqlx::FromRow;
 use ts_rs::TS;
 
+use super::exit_info::ExitInfo;
+use crate::container::ResourceLimits;
+use crate::net::authority;
+
 /// Session status.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, sqlx::Type, TS)]
 #[serde(rename_all = "lowercase")]
@@ -95,6 +99,22 @@ impl TryFrom<String> for RuntimeMode {
     }
 }
 
+/// A lifecycle change of a session, announced by the repository when it is
+/// recorded. Hibernating counts as stopping.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SessionLifecycle {
+    Created,
+    Started,
+    Stopped,
+    Failed { error: String },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SessionLifecycleEvent {
+    pub session_id: String,
+    pub lifecycle: SessionLifecycle,
+}
+
 /// A container session.
 #[derive(Debug, Clone, Serialize, Deserialize, FromRow, TS)]
 #[ts(export, export_to = "../../../../frontend/src/generated/")]
@@ -157,6 +177,58 @@ pub struct Session {
     pub last_activity_at: Option<String>,
     /// Error message if failed.
     pub error_message: Option<String>,
+    /// Why the session last stopped, when known. Cleared when it runs again.
+    #[sqlx(json(nullable))]
+    #[serde(default)]
+    pub exit_info: Option<ExitInfo>,
+    /// Resource limits of the session's container (container mode only).
+    #[sqlx(json(nullable))]
+    #[serde(default)]
+    pub resource_limits: Option<ResourceLimits>,
+    /// State saved when the session was hibernated, restored by the next
+    /// resume.
+    #[sqlx(json(nullable))]
+    #[serde(default)]
+    pub hibernation: Option<Hibernation>,
+}
+
+/// What the idle cleanup does with sessions past the idle timeout.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum IdleAction {
+    /// Stop the session; resuming starts its services afresh.
+    #[default]
+    Stop,
+    /// Checkpoint the session before stopping it, so resuming restores the
+    /// agent conversations (and, in container mode, the running shells).
+    Hibernate,
+}
+
+/// State saved when a session was hibernated.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "../../../../frontend/src/generated/")]
+pub struct Hibernation {
+    /// When the session was hibernated.
+    pub hibernated_at: String,
+    /// Container checkpoint holding the session's processes (container mode).
+    pub checkpoint: Option<String>,
+    /// Agent sessions that ran in the workspace (local mode). They are
+    /// resumed from their session files.
+    #[serde(default)]
+    pub agents: Vec<HibernatedAgent>,
+}
+
+/// An agent session running when its workspace session was hibernated.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "../../../../frontend/src/generated/")]
+pub struct HibernatedAgent {
+    pub session_id: String,
+    pub cwd: String,
+    pub harness: Option<String>,
+    pub provider: Option<String>,
+    pub model: Option<String>,
+    #[serde(default)]
+    pub sandbox_profile: Option<String>,
 }
 
 fn default_max_agents() -> Option<i64> {
@@ -207,6 +279,10 @@ pub struct CreateSessionRequest {
     /// Environment variables to inject.
     #[serde(default)]
     pub env: std::collections::HashMap<String, String>,
+    /// Sandbox profile whose egress rules apply to the container, nested in
+    /// sandbox.toml so it can only add restrictions.
+    #[serde(default)]
+    pub sandbox_profile: Option<String>,
 }
 
 /// Response from session creation.
@@ -252,9 +328,9 @@ impl Session {
     #[allow(dead_code)]
     pub fn urls(&self, host: &str) -> SessionUrls {
         SessionUrls {
-            agent: format!("http://{}:{}", host, self.agent_port),
-            fileserver: format!("http://{}:{}", host, self.fileserver_port),
-            terminal: format!("ws://{}:{}", host, self.ttyd_port),
+            agent: format!("http://{}", authority(host, self.agent_port)),
+            fileserver: format!("http://{}", authority(host, self.fileserver_port)),
+            terminal: format!("ws://{}", authority(host, self.ttyd_port)),
         }
     }
 }
@@ -267,3 +343,103 @@ impl TryFrom<String> for SessionStatus {
         value.parse()
     }
 }
+
+/// Where a cloned session's workspace comes from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CloneWorkspace {
+    /// Work in the source session's workspace directory.
+    #[default]
+    Shared,
+    /// Copy the source workspace into a new directory.
+    Copy,
+    /// Start in a new, empty directory.
+    Clean,
+}
+
+impl CloneWorkspace {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            CloneWorkspace::Shared => "shared",
+            CloneWorkspace::Copy => "copy",
+            CloneWorkspace::Clean => "clean",
+        }
+    }
+}
+
+impl std::str::FromStr for CloneWorkspace {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "shared" => Ok(CloneWorkspace::Shared),
+            "copy" => Ok(CloneWorkspace::Copy),
+            "clean" => Ok(CloneWorkspace::Clean),
+            _ => Err(format!("unknown clone workspace mode: {}", s)),
+        }
+    }
+}
+
+/// Request to clone a session. Unset fields keep the source session's
+/// value.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct CloneSessionRequest {
+    /// How the clone gets its workspace (default: shared).
+    #[serde(default)]
+    pub workspace: CloneWorkspace,
+    #[serde(default)]
+    pub image: Option<String>,
+    #[serde(default)]
+    pub agent: Option<String>,
+    #[serde(default)]
+    pub provider: Option<String>,
+    #[serde(default)]
+    pub model: Option<String>,
+    /// Environment variables added to the source session's. Variables the
+    /// server sets itself cannot be overridden.
+    #[serde(default)]
+    pub env: std::collections::HashMap<String, String>,
+}
+
+/// Setup of a session beyond its container: the model, extra environment
+/// variables, and the session it was cloned from.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct SessionSetup {
+    pub session_id: String,
+    /// Source session, if this one is a clone. Kept when the source is
+    /// deleted.
+    pub cloned_from: Option<String>,
+    pub workspace: Option<CloneWorkspace>,
+    pub provider: Option<String>,
+    pub model: Option<String>,
+    pub env: std::collections::HashMap<String, String>,
+    /// Extra directories mounted into the session's container.
+    #[serde(default)]
+    pub mounts: Vec<SessionMount>,
+    /// Sandbox profile whose egress rules apply to the session's container.
+    #[serde(default)]
+    pub sandbox_profile: Option<String>,
+    pub created_at: String,
+}
+
+/// A host directory mounted into a container session.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "../../../../frontend/src/generated/")]
+pub struct SessionMount {
+    /// Directory on the host; must lie in the user's workspace roots.
+    pub source: String,
+    /// Absolute path inside the container.
+    pub target: String,
+    #[serde(default)]
+    pub read_only: bool,
+}
+
+/// Resource limits a user's new container sessions get.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct UserResourceLimits {
+    pub user_id: String,
+    /// Limits an admin set for the user, if any.
+    pub overrides: Option<ResourceLimits>,
+    /// The overrides with `[container.resources]` filling the unset limits.
+    pub effective: ResourceLimits,
+}
@@ -0,0 +1,104 @@
+//! Recovery from port conflicts on session start.
+//!
+//! Session ports are allocated from the database and probed before a session
+//! is created or resumed, but another program can still bind one in between.
+//! When a start fails and one of the session's ports is then held by
+//! something else (or the runtime reports the address as taken), the session
+//! service moves the session to the next free port range and tries again.
+//! Each move is announced as a [`PortConflictEvent`] naming the process that
+//! holds the port, when `ss` can tell (other users' processes need root).
+
+use serde::Serialize;
+
+/// A process listening on a port a session needed.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct PortHolder {
+    pub pid: u32,
+    /// Process name as `ss` reports it.
+    pub name: String,
+}
+
+impl std::fmt::Display for PortHolder {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} (pid {})", self.name, self.pid)
+    }
+}
+
+/// A session start that failed because a port was taken.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PortConflict {
+    /// The taken port, when it could be told which one.
+    pub port: Option<u16>,
+    pub holder: Option<PortHolder>,
+}
+
+/// Announced when a session was moved to other ports after a conflict.
+#[derive(Debug, Clone, Serialize)]
+pub struct PortConflictEvent {
+    pub session_id: String,
+    pub user_id: String,
+    pub port: Option<u16>,
+    pub holder: Option<PortHolder>,
+    /// Agent, fileserver and ttyd ports before and after the move.
+    pub previous_ports: [i64; 3],
+    pub ports: [i64; 3],
+    /// Retry number, from 1.
+    pub attempt: u32,
+}
+
+/// Whether an error says that an address or port was already taken.
+pub fn mentions_port_conflict(error: &anyhow::Error) -> bool {
+    error.chain().any(|cause| {
+        let message = cause.to_string().to_lowercase();
+        message.contains("address already in use")
+            || message.contains("port is already allocated")
+            || message.contains("eaddrinuse")
+    })
+}
+
+/// Work out whether a failed start was a port conflict: one of `ports` is
+/// held by another program now, or the error says so.
+pub fn detect(ports: &[u16], error: &anyhow::Error) -> Option<PortConflict> {
+    let busy = ports
+        .iter()
+        .copied()
+        .find(|port| !crate::local::is_port_available(*port));
+    match busy {
+        Some(port) => Some(PortConflict {
+            port: Some(port),
+            holder: crate::local::find_process_on_port(port)
+                .map(|(pid, name)| PortHolder { pid, name }),
+        }),
+        None if mentions_port_conflict(error) => Some(PortConflict {
+            port: None,
+            holder: None,
+        }),
+        None => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn conflicts_are_recognized_in_errors_and_by_held_ports() {
+        let error = anyhow::anyhow!("Bind for 0.0.0.0:41821 failed: port is already allocated")
+            .context("creating container");
+        assert!(mentions_port_conflict(&error));
+        assert!(!mentions_port_conflict(&anyhow::anyhow!("image not found")));
+
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let conflict = detect(&[port], &anyhow::anyhow!("services not ready")).unwrap();
+        assert_eq!(conflict.port, Some(port));
+        if let Some(holder) = conflict.holder {
+            assert_eq!(holder.pid, std::process::id());
+        }
+        drop(listener);
+        assert_eq!(
+            detect(&[port], &anyhow::anyhow!("services not ready")),
+            None
+        );
+    }
+}
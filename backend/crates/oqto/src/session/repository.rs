@@ -1,44 +1,116 @@
 //! Session database repository.
 
 use anyhow::{Context, Result};
-use sqlx::SqlitePool;
+use tokio::sync::broadcast;
 
-use super::models::{Session, SessionStatus};
+use super::exit_info::ExitInfo;
+use super::models::{
+    Hibernation, Session, SessionLifecycle, SessionLifecycleEvent, SessionSetup, SessionStatus,
+};
+use crate::container::ResourceLimits;
+use crate::db::{self, DbPool, on_pool};
 
 /// All session columns for SELECT queries.
 const SESSION_COLUMNS: &str = r#"
     id, readable_id, container_id, container_name, user_id, workspace_path, agent, image, image_digest,
     agent_port, fileserver_port, ttyd_port, eavs_port, agent_base_port, max_agents,
     eavs_key_id, eavs_key_hash, eavs_virtual_key, mmry_port,
-    status, runtime_mode, created_at, started_at, stopped_at, last_activity_at, error_message
+    status, runtime_mode, created_at, started_at, stopped_at, last_activity_at, error_message,
+    exit_info, resource_limits, hibernation
 "#;
 
+/// Lifecycle events buffered for slow subscribers.
+const LIFECYCLE_BUFFER_SIZE: usize = 256;
+
+#[derive(Debug, Clone, sqlx::FromRow)]
+struct SessionSetupRow {
+    session_id: String,
+    cloned_from: Option<String>,
+    workspace: Option<String>,
+    provider: Option<String>,
+    model: Option<String>,
+    env: String,
+    mounts: Option<String>,
+    sandbox_profile: Option<String>,
+    created_at: String,
+}
+
+impl TryFrom<SessionSetupRow> for SessionSetup {
+    type Error = anyhow::Error;
+
+    fn try_from(row: SessionSetupRow) -> Result<Self> {
+        let env = serde_json::from_str(&row.env)
+            .with_context(|| format!("parsing env of session {}", row.session_id))?;
+        let workspace = row
+            .workspace
+            .map(|w| w.parse().map_err(anyhow::Error::msg))
+            .transpose()?;
+        let mounts = row
+            .mounts
+            .map(|m| serde_json::from_str(&m))
+            .transpose()
+            .with_context(|| format!("parsing mounts of session {}", row.session_id))?
+            .unwrap_or_default();
+        Ok(Self {
+            session_id: row.session_id,
+            cloned_from: row.cloned_from,
+            workspace,
+            provider: row.provider,
+            model: row.model,
+            env,
+            mounts,
+            sandbox_profile: row.sandbox_profile,
+            created_at: row.created_at,
+        })
+    }
+}
+
 /// Repository for session persistence.
 #[derive(Debug, Clone)]
 pub struct SessionRepository {
-    pool: SqlitePool,
+    pool: DbPool,
+    lifecycle: broadcast::Sender<SessionLifecycleEvent>,
 }
 
 impl SessionRepository {
     /// Create a new repository.
-    pub fn new(pool: SqlitePool) -> Self {
-        Self { pool }
+    pub fn new(pool: DbPool) -> Self {
+        Self {
+            pool,
+            lifecycle: broadcast::channel(LIFECYCLE_BUFFER_SIZE).0,
+        }
     }
 
-    pub fn pool(&self) -> &SqlitePool {
+    pub fn pool(&self) -> &DbPool {
         &self.pool
     }
 
+    /// Subscribe to sessions being created, started, stopped and failing,
+    /// as the changes are recorded.
+    pub fn subscribe_lifecycle(&self) -> broadcast::Receiver<SessionLifecycleEvent> {
+        self.lifecycle.subscribe()
+    }
+
+    fn announce(&self, rows_affected: u64, session_id: &str, lifecycle: SessionLifecycle) {
+        if rows_affected > 0 {
+            let _ = self.lifecycle.send(SessionLifecycleEvent {
+                session_id: session_id.to_string(),
+                lifecycle,
+            });
+        }
+    }
+
     /// Create a new session.
     pub async fn create(&self, session: &Session) -> Result<()> {
-        sqlx::query(
+        on_pool!(&self.pool, |pool| sqlx::query(
             r#"
             INSERT INTO sessions (
                 id, readable_id, container_id, container_name, user_id, workspace_path, agent, image, image_digest,
                 agent_port, fileserver_port, ttyd_port, eavs_port, agent_base_port, max_agents,
                 eavs_key_id, eavs_key_hash, eavs_virtual_key, mmry_port,
-                status, runtime_mode, created_at, started_at, stopped_at, last_activity_at, error_message
-            ) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+                status, runtime_mode, created_at, started_at, stopped_at, last_activity_at, error_message,
+                exit_info, resource_limits, hibernation
+            ) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15, $16, $17, $18, $19, $20, $21, $22, $23, $24, $25, $26, $27, $28, $29)
             "#,
         )
         .bind(&session.id)
@@ -67,10 +139,15 @@ impl SessionRepository {
         .bind(&session.stopped_at)
         .bind(&session.last_activity_at)
         .bind(&session.error_message)
-        .execute(&self.pool)
+        .bind(session.exit_info.as_ref().map(sqlx::types::Json))
+        .bind(session.resource_limits.as_ref().map(sqlx::types::Json))
+        .bind(session.hibernation.as_ref().map(sqlx::types::Json))
+        .execute(pool)
         .await
+        .map(|r| r.rows_affected()))
         .context("creating session")?;
 
+        self.announce(1, &session.id, SessionLifecycle::Created);
         Ok(())
     }
 
@@ -81,18 +158,19 @@ impl SessionRepository {
         key_id: Option<&str>,
         key_hash: Option<&str>,
     ) -> Result<()> {
-        sqlx::query(
+        on_pool!(&self.pool, |pool| sqlx::query(
             r#"
             UPDATE sessions
-            SET eavs_key_id = ?, eavs_key_hash = ?
-            WHERE id = ?
+            SET eavs_key_id = $1, eavs_key_hash = $2
+            WHERE id = $3
             "#,
         )
         .bind(key_id)
         .bind(key_hash)
         .bind(session_id)
-        .execute(&self.pool)
+        .execute(pool)
         .await
+        .map(|r| r.rows_affected()))
         .context("updating session eavs keys")?;
 
         Ok(())
@@ -100,12 +178,12 @@ impl SessionRepository {
 
     /// Get a session by ID.
     pub async fn get(&self, id: &str) -> Result<Option<Session>> {
-        let query = format!("SELECT {} FROM sessions WHERE id = ?", SESSION_COLUMNS);
-        let session = sqlx::query_as::<_, Session>(&query)
+        let query = format!("SELECT {} FROM sessions WHERE id = $1", SESSION_COLUMNS);
+        let session = on_pool!(&self.pool, |pool| sqlx::query_as::<_, Session>(&query)
             .bind(id)
-            .fetch_optional(&self.pool)
-            .await
-            .context("fetching session")?;
+            .fetch_optional(pool)
+            .await)
+        .context("fetching session")?;
 
         Ok(session)
     }
@@ -114,14 +192,14 @@ impl SessionRepository {
     #[allow(dead_code)]
     pub async fn get_by_container_id(&self, container_id: &str) -> Result<Option<Session>> {
         let query = format!(
-            "SELECT {} FROM sessions WHERE container_id = ?",
+            "SELECT {} FROM sessions WHERE container_id = $1",
             SESSION_COLUMNS
         );
-        let session = sqlx::query_as::<_, Session>(&query)
+        let session = on_pool!(&self.pool, |pool| sqlx::query_as::<_, Session>(&query)
             .bind(container_id)
-            .fetch_optional(&self.pool)
-            .await
-            .context("fetching session by container ID")?;
+            .fetch_optional(pool)
+            .await)
+        .context("fetching session by container ID")?;
 
         Ok(session)
     }
@@ -132,10 +210,10 @@ impl SessionRepository {
             "SELECT {} FROM sessions ORDER BY created_at DESC",
             SESSION_COLUMNS
         );
-        let sessions = sqlx::query_as::<_, Session>(&query)
-            .fetch_all(&self.pool)
-            .await
-            .context("listing sessions")?;
+        let sessions = on_pool!(&self.pool, |pool| sqlx::query_as::<_, Session>(&query)
+            .fetch_all(pool)
+            .await)
+        .context("listing sessions")?;
 
         Ok(sessions)
     }
@@ -147,10 +225,10 @@ impl SessionRepository {
             "SELECT {} FROM sessions WHERE status IN ('pending', 'starting', 'running') ORDER BY created_at DESC",
             SESSION_COLUMNS
         );
-        let sessions = sqlx::query_as::<_, Session>(&query)
-            .fetch_all(&self.pool)
-            .await
-            .context("listing active sessions")?;
+        let sessions = on_pool!(&self.pool, |pool| sqlx::query_as::<_, Session>(&query)
+            .fetch_all(pool)
+            .await)
+        .context("listing active sessions")?;
 
         Ok(sessions)
     }
@@ -158,29 +236,29 @@ impl SessionRepository {
     /// List running sessions for a user.
     pub async fn list_running_for_user(&self, user_id: &str) -> Result<Vec<Session>> {
         let query = format!(
-            "SELECT {} FROM sessions WHERE user_id = ? AND status = 'running' ORDER BY created_at DESC",
+            "SELECT {} FROM sessions WHERE user_id = $1 AND status = 'running' ORDER BY created_at DESC",
             SESSION_COLUMNS
         );
-        let sessions = sqlx::query_as::<_, Session>(&query)
+        let sessions = on_pool!(&self.pool, |pool| sqlx::query_as::<_, Session>(&query)
             .bind(user_id)
-            .fetch_all(&self.pool)
-            .await
-            .context("listing running sessions for user")?;
+            .fetch_all(pool)
+            .await)
+        .context("listing running sessions for user")?;
 
         Ok(sessions)
     }
 
     pub async fn list_for_user(&self, user_id: &str) -> Result<Vec<Session>> {
-        let sessions = sqlx::query_as::<_, Session>(
+        let sessions = on_pool!(&self.pool, |pool| sqlx::query_as::<_, Session>(
             r#"
             SELECT * FROM sessions
-            WHERE user_id = ?
+            WHERE user_id = $1
             ORDER BY created_at DESC
             "#,
         )
         .bind(user_id)
-        .fetch_all(&self.pool)
-        .await
+        .fetch_all(pool)
+        .await)
         .context("listing sessions for user")?;
 
         Ok(sessions)
@@ -190,39 +268,44 @@ impl SessionRepository {
     #[allow(dead_code)]
     pub async fn list_by_user(&self, user_id: &str) -> Result<Vec<Session>> {
         let query = format!(
-            "SELECT {} FROM sessions WHERE user_id = ? ORDER BY created_at DESC",
+            "SELECT {} FROM sessions WHERE user_id = $1 ORDER BY created_at DESC",
             SESSION_COLUMNS
         );
-        let sessions = sqlx::query_as::<_, Session>(&query)
+        let sessions = on_pool!(&self.pool, |pool| sqlx::query_as::<_, Session>(&query)
             .bind(user_id)
-            .fetch_all(&self.pool)
-            .await
-            .context("listing sessions by user")?;
+            .fetch_all(pool)
+            .await)
+        .context("listing sessions by user")?;
 
         Ok(sessions)
     }
 
     /// Update session status.
     pub async fn update_status(&self, id: &str, status: SessionStatus) -> Result<()> {
-        sqlx::query("UPDATE sessions SET status = ? WHERE id = ?")
-            .bind(status.to_string())
-            .bind(id)
-            .execute(&self.pool)
-            .await
-            .context("updating session status")?;
+        on_pool!(&self.pool, |pool| sqlx::query(
+            "UPDATE sessions SET status = $1 WHERE id = $2"
+        )
+        .bind(status.to_string())
+        .bind(id)
+        .execute(pool)
+        .await
+        .map(|r| r.rows_affected()))
+        .context("updating session status")?;
 
         Ok(())
     }
 
     /// Update session with container ID and mark as starting.
     pub async fn set_container_id(&self, id: &str, container_id: &str) -> Result<()> {
-        sqlx::query(
-            "UPDATE sessions SET container_id = ?, status = 'starting', started_at = datetime('now') WHERE id = ?",
+        on_pool!(&self.pool, |pool| sqlx::query(
+            "UPDATE sessions SET container_id = $1, status = 'starting', started_at = $2 WHERE id = $3",
         )
         .bind(container_id)
+        .bind(db::now())
         .bind(id)
-        .execute(&self.pool)
+        .execute(pool)
         .await
+        .map(|r| r.rows_affected()))
         .context("setting container ID")?;
 
         Ok(())
@@ -238,8 +321,8 @@ impl SessionRepository {
         mmry_port: Option<i64>,
         agent_base_port: Option<i64>,
     ) -> Result<()> {
-        sqlx::query(
-            "UPDATE sessions SET agent_port = ?, fileserver_port = ?, ttyd_port = ?, mmry_port = ?, agent_base_port = ? WHERE id = ?",
+        on_pool!(&self.pool, |pool| sqlx::query(
+            "UPDATE sessions SET agent_port = $1, fileserver_port = $2, ttyd_port = $3, mmry_port = $4, agent_base_port = $5 WHERE id = $6",
         )
         .bind(agent_port)
         .bind(fileserver_port)
@@ -247,8 +330,9 @@ impl SessionRepository {
         .bind(mmry_port)
         .bind(agent_base_port)
         .bind(id)
-        .execute(&self.pool)
+        .execute(pool)
         .await
+        .map(|r| r.rows_affected()))
         .context("updating session ports")?;
 
         Ok(())
@@ -256,61 +340,225 @@ impl SessionRepository {
 
     /// Set the mmry port for a session.
     pub async fn set_mmry_port(&self, id: &str, mmry_port: Option<i64>) -> Result<()> {
-        sqlx::query("UPDATE sessions SET mmry_port = ? WHERE id = ?")
-            .bind(mmry_port)
-            .bind(id)
-            .execute(&self.pool)
-            .await
-            .context("setting mmry port")?;
+        on_pool!(&self.pool, |pool| sqlx::query(
+            "UPDATE sessions SET mmry_port = $1 WHERE id = $2"
+        )
+        .bind(mmry_port)
+        .bind(id)
+        .execute(pool)
+        .await
+        .map(|r| r.rows_affected()))
+        .context("setting mmry port")?;
 
         Ok(())
     }
 
-    /// Mark session as running.
+    /// Set the secret the session's fileserver checks capability tokens
+    /// against.
+    pub async fn set_fileserver_token_secret(&self, id: &str, secret: &str) -> Result<()> {
+        on_pool!(&self.pool, |pool| sqlx::query(
+            "UPDATE sessions SET fileserver_token_secret = $1 WHERE id = $2"
+        )
+        .bind(secret)
+        .bind(id)
+        .execute(pool)
+        .await
+        .map(|r| r.rows_affected()))
+        .context("setting fileserver token secret")?;
+
+        Ok(())
+    }
+
+    /// Get the fileserver token secret of a session. Kept out of `Session`
+    /// so it is never serialized to clients.
+    pub async fn fileserver_token_secret(&self, id: &str) -> Result<Option<String>> {
+        let secret: Option<Option<String>> = on_pool!(&self.pool, |pool| sqlx::query_scalar(
+            "SELECT fileserver_token_secret FROM sessions WHERE id = $1"
+        )
+        .bind(id)
+        .fetch_optional(pool)
+        .await)
+        .context("getting fileserver token secret")?;
+
+        Ok(secret.flatten())
+    }
+
+    /// Mark session as running. Clears the exit info of an earlier run.
     pub async fn mark_running(&self, id: &str) -> Result<()> {
-        sqlx::query("UPDATE sessions SET status = 'running' WHERE id = ?")
-            .bind(id)
-            .execute(&self.pool)
-            .await
-            .context("marking session running")?;
+        let rows = on_pool!(&self.pool, |pool| sqlx::query(
+            "UPDATE sessions SET status = 'running', exit_info = NULL, hibernation = NULL WHERE id = $1"
+        )
+        .bind(id)
+        .execute(pool)
+        .await
+        .map(|r| r.rows_affected()))
+        .context("marking session running")?;
 
+        self.announce(rows, id, SessionLifecycle::Started);
         Ok(())
     }
 
     /// Mark session as stopped.
     pub async fn mark_stopped(&self, id: &str) -> Result<()> {
-        sqlx::query(
-            "UPDATE sessions SET status = 'stopped', stopped_at = datetime('now') WHERE id = ?",
+        let rows = on_pool!(&self.pool, |pool| sqlx::query(
+            "UPDATE sessions SET status = 'stopped', stopped_at = $1 WHERE id = $2",
         )
+        .bind(db::now())
         .bind(id)
-        .execute(&self.pool)
+        .execute(pool)
         .await
+        .map(|r| r.rows_affected()))
         .context("marking session stopped")?;
 
+        self.announce(rows, id, SessionLifecycle::Stopped);
         Ok(())
     }
 
-    /// Mark session as failed with error message.
+    /// Mark session as stopped with the state a resume restores.
+    pub async fn mark_hibernated(&self, id: &str, hibernation: &Hibernation) -> Result<()> {
+        let rows = on_pool!(&self.pool, |pool| sqlx::query(
+            "UPDATE sessions SET status = 'stopped', stopped_at = $1, hibernation = $2 WHERE id = $3",
+        )
+        .bind(db::now())
+        .bind(sqlx::types::Json(hibernation))
+        .bind(id)
+        .execute(pool)
+        .await
+        .map(|r| r.rows_affected()))
+        .context("marking session hibernated")?;
+
+        self.announce(rows, id, SessionLifecycle::Stopped);
+        Ok(())
+    }
+
+    /// Mark session as failed with error message, classifying the message
+    /// for the exit info.
     pub async fn mark_failed(&self, id: &str, error: &str) -> Result<()> {
-        sqlx::query(
-            "UPDATE sessions SET status = 'failed', stopped_at = datetime('now'), error_message = ? WHERE id = ?",
+        self.mark_failed_with_exit(id, error, &ExitInfo::from_error(error))
+            .await
+    }
+
+    /// Mark session as failed with error message and exit diagnostics.
+    pub async fn mark_failed_with_exit(
+        &self,
+        id: &str,
+        error: &str,
+        exit_info: &ExitInfo,
+    ) -> Result<()> {
+        let rows = on_pool!(&self.pool, |pool| sqlx::query(
+            "UPDATE sessions SET status = 'failed', stopped_at = $1, error_message = $2, exit_info = $3 WHERE id = $4",
         )
+        .bind(db::now())
         .bind(error)
+        .bind(sqlx::types::Json(exit_info))
         .bind(id)
-        .execute(&self.pool)
+        .execute(pool)
         .await
+        .map(|r| r.rows_affected()))
         .context("marking session failed")?;
 
+        self.announce(
+            rows,
+            id,
+            SessionLifecycle::Failed {
+                error: error.to_string(),
+            },
+        );
+        Ok(())
+    }
+
+    /// Record why a session stopped without marking it failed.
+    pub async fn set_exit_info(&self, id: &str, exit_info: &ExitInfo) -> Result<()> {
+        on_pool!(&self.pool, |pool| sqlx::query(
+            "UPDATE sessions SET exit_info = $1 WHERE id = $2"
+        )
+        .bind(sqlx::types::Json(exit_info))
+        .bind(id)
+        .execute(pool)
+        .await
+        .map(|r| r.rows_affected()))
+        .context("setting session exit info")?;
+
+        Ok(())
+    }
+
+    /// Replace the resource limits recorded for a session.
+    pub async fn set_resource_limits(&self, id: &str, limits: &ResourceLimits) -> Result<()> {
+        on_pool!(&self.pool, |pool| sqlx::query(
+            "UPDATE sessions SET resource_limits = $1 WHERE id = $2"
+        )
+        .bind(sqlx::types::Json(limits))
+        .bind(id)
+        .execute(pool)
+        .await
+        .map(|r| r.rows_affected()))
+        .context("setting session resource limits")?;
+
         Ok(())
     }
 
+    /// Resource limits an admin set as defaults for a user's sessions.
+    pub async fn get_user_resource_limits(&self, user_id: &str) -> Result<Option<ResourceLimits>> {
+        let limits: Option<sqlx::types::Json<ResourceLimits>> =
+            on_pool!(&self.pool, |pool| sqlx::query_scalar(
+                "SELECT limits FROM user_resource_limits WHERE user_id = $1"
+            )
+            .bind(user_id)
+            .fetch_optional(pool)
+            .await)
+            .context("fetching user resource limits")?;
+
+        Ok(limits.map(|l| l.0))
+    }
+
+    /// Set the default resource limits of a user's sessions.
+    pub async fn set_user_resource_limits(
+        &self,
+        user_id: &str,
+        limits: &ResourceLimits,
+    ) -> Result<()> {
+        on_pool!(&self.pool, |pool| sqlx::query(
+            r#"INSERT INTO user_resource_limits (user_id, limits, updated_at)
+               VALUES ($1, $2, $3)
+               ON CONFLICT(user_id) DO UPDATE SET
+                   limits = excluded.limits,
+                   updated_at = excluded.updated_at"#
+        )
+        .bind(user_id)
+        .bind(sqlx::types::Json(limits))
+        .bind(db::now())
+        .execute(pool)
+        .await
+        .map(|r| r.rows_affected()))
+        .context("setting user resource limits")?;
+
+        Ok(())
+    }
+
+    /// Remove a user's default resource limits. Returns whether any were set.
+    pub async fn delete_user_resource_limits(&self, user_id: &str) -> Result<bool> {
+        let deleted = on_pool!(&self.pool, |pool| sqlx::query(
+            "DELETE FROM user_resource_limits WHERE user_id = $1"
+        )
+        .bind(user_id)
+        .execute(pool)
+        .await
+        .map(|r| r.rows_affected()))
+        .context("deleting user resource limits")?;
+
+        Ok(deleted > 0)
+    }
+
     /// Delete a session.
     pub async fn delete(&self, id: &str) -> Result<()> {
-        sqlx::query("DELETE FROM sessions WHERE id = ?")
-            .bind(id)
-            .execute(&self.pool)
-            .await
-            .context("deleting session")?;
+        on_pool!(&self.pool, |pool| sqlx::query(
+            "DELETE FROM sessions WHERE id = $1"
+        )
+        .bind(id)
+        .execute(pool)
+        .await
+        .map(|r| r.rows_affected()))
+        .context("deleting session")?;
 
         Ok(())
     }
@@ -320,14 +568,14 @@ impl SessionRepository {
     /// Returns the most recently stopped session for the user that still has a container/PIDs.
     pub async fn find_resumable_session(&self, user_id: &str) -> Result<Option<Session>> {
         let query = format!(
-            "SELECT {} FROM sessions WHERE user_id = ? AND status = 'stopped' AND container_id IS NOT NULL ORDER BY stopped_at DESC LIMIT 1",
+            "SELECT {} FROM sessions WHERE user_id = $1 AND status = 'stopped' AND container_id IS NOT NULL ORDER BY stopped_at DESC LIMIT 1",
             SESSION_COLUMNS
         );
-        let session = sqlx::query_as::<_, Session>(&query)
+        let session = on_pool!(&self.pool, |pool| sqlx::query_as::<_, Session>(&query)
             .bind(user_id)
-            .fetch_optional(&self.pool)
-            .await
-            .context("finding resumable session")?;
+            .fetch_optional(pool)
+            .await)
+        .context("finding resumable session")?;
 
         Ok(session)
     }
@@ -337,26 +585,30 @@ impl SessionRepository {
     /// Used for cleanup of old stopped containers.
     pub async fn list_stale_stopped_sessions(&self, older_than_hours: i64) -> Result<Vec<Session>> {
         let query = format!(
-            "SELECT {} FROM sessions WHERE status = 'stopped' AND container_id IS NOT NULL AND stopped_at < datetime('now', ? || ' hours') ORDER BY stopped_at ASC",
+            "SELECT {} FROM sessions WHERE status = 'stopped' AND container_id IS NOT NULL AND stopped_at < $1 ORDER BY stopped_at ASC",
             SESSION_COLUMNS
         );
-        let sessions = sqlx::query_as::<_, Session>(&query)
-            .bind(-older_than_hours) // negative for "X hours ago"
-            .fetch_all(&self.pool)
-            .await
-            .context("listing stale stopped sessions")?;
+        let cutoff = db::timestamp(chrono::Utc::now() - chrono::Duration::hours(older_than_hours));
+        let sessions = on_pool!(&self.pool, |pool| sqlx::query_as::<_, Session>(&query)
+            .bind(cutoff)
+            .fetch_all(pool)
+            .await)
+        .context("listing stale stopped sessions")?;
 
         Ok(sessions)
     }
 
     /// Update the image digest for a session.
     pub async fn update_image_digest(&self, id: &str, digest: &str) -> Result<()> {
-        sqlx::query("UPDATE sessions SET image_digest = ? WHERE id = ?")
-            .bind(digest)
-            .bind(id)
-            .execute(&self.pool)
-            .await
-            .context("updating image digest")?;
+        on_pool!(&self.pool, |pool| sqlx::query(
+            "UPDATE sessions SET image_digest = $1 WHERE id = $2"
+        )
+        .bind(digest)
+        .bind(id)
+        .execute(pool)
+        .await
+        .map(|r| r.rows_affected()))
+        .context("updating image digest")?;
 
         Ok(())
     }
@@ -368,52 +620,77 @@ impl SessionRepository {
         image: &str,
         digest: Option<&str>,
     ) -> Result<()> {
-        sqlx::query("UPDATE sessions SET image = ?, image_digest = ? WHERE id = ?")
-            .bind(image)
-            .bind(digest)
-            .bind(id)
-            .execute(&self.pool)
-            .await
-            .context("updating image and digest")?;
+        on_pool!(&self.pool, |pool| sqlx::query(
+            "UPDATE sessions SET image = $1, image_digest = $2 WHERE id = $3"
+        )
+        .bind(image)
+        .bind(digest)
+        .bind(id)
+        .execute(pool)
+        .await
+        .map(|r| r.rows_affected()))
+        .context("updating image and digest")?;
 
         Ok(())
     }
 
     /// Clear container ID (used when recreating container during upgrade).
     pub async fn clear_container_id(&self, id: &str) -> Result<()> {
-        sqlx::query("UPDATE sessions SET container_id = NULL WHERE id = ?")
-            .bind(id)
-            .execute(&self.pool)
-            .await
-            .context("clearing container ID")?;
+        on_pool!(&self.pool, |pool| sqlx::query(
+            "UPDATE sessions SET container_id = NULL WHERE id = $1"
+        )
+        .bind(id)
+        .execute(pool)
+        .await
+        .map(|r| r.rows_affected()))
+        .context("clearing container ID")?;
 
         Ok(())
     }
 
     /// Update last activity timestamp for a session.
     pub async fn touch_activity(&self, id: &str) -> Result<()> {
-        sqlx::query("UPDATE sessions SET last_activity_at = datetime('now') WHERE id = ?")
-            .bind(id)
-            .execute(&self.pool)
-            .await
-            .context("updating last activity")?;
+        on_pool!(&self.pool, |pool| sqlx::query(
+            "UPDATE sessions SET last_activity_at = $1 WHERE id = $2"
+        )
+        .bind(db::now())
+        .bind(id)
+        .execute(pool)
+        .await
+        .map(|r| r.rows_affected()))
+        .context("updating last activity")?;
 
         Ok(())
     }
 
     /// List running sessions that have been idle for longer than the given duration.
+    ///
+    /// Least recently active first; sessions without activity come first.
     pub async fn list_idle_sessions(&self, idle_minutes: i64) -> Result<Vec<Session>> {
         let query = format!(
-            "SELECT {} FROM sessions WHERE status = 'running' AND (last_activity_at IS NULL OR datetime(last_activity_at) < datetime('now', ? || ' minutes')) ORDER BY datetime(last_activity_at) ASC",
+            "SELECT {} FROM sessions WHERE status = 'running'",
             SESSION_COLUMNS
         );
-        let sessions = sqlx::query_as::<_, Session>(&query)
-            .bind(-idle_minutes) // negative for "X minutes ago"
-            .fetch_all(&self.pool)
-            .await
-            .context("listing idle sessions")?;
-
-        Ok(sessions)
+        let sessions = on_pool!(&self.pool, |pool| sqlx::query_as::<_, Session>(&query)
+            .fetch_all(pool)
+            .await)
+        .context("listing idle sessions")?;
+
+        // `last_activity_at` is RFC 3339 at creation and `db::now()` format
+        // afterwards, so it is compared parsed rather than in SQL.
+        let cutoff = chrono::Utc::now() - chrono::Duration::minutes(idle_minutes);
+        let mut idle: Vec<_> = sessions
+            .into_iter()
+            .map(|s| {
+                (
+                    s.last_activity_at.as_deref().and_then(db::parse_timestamp),
+                    s,
+                )
+            })
+            .filter(|(activity, _)| activity.is_none_or(|at| at < cutoff))
+            .collect();
+        idle.sort_by_key(|(activity, _)| *activity);
+        Ok(idle.into_iter().map(|(_, s)| s).collect())
     }
 
     /// Find a running session for a specific workspace path.
@@ -423,15 +700,15 @@ impl SessionRepository {
         workspace_path: &str,
     ) -> Result<Option<Session>> {
         let query = format!(
-            "SELECT {} FROM sessions WHERE user_id = ? AND workspace_path = ? AND status = 'running' LIMIT 1",
+            "SELECT {} FROM sessions WHERE user_id = $1 AND workspace_path = $2 AND status = 'running' LIMIT 1",
             SESSION_COLUMNS
         );
-        let session = sqlx::query_as::<_, Session>(&query)
+        let session = on_pool!(&self.pool, |pool| sqlx::query_as::<_, Session>(&query)
             .bind(user_id)
             .bind(workspace_path)
-            .fetch_optional(&self.pool)
-            .await
-            .context("finding running session for workspace")?;
+            .fetch_optional(pool)
+            .await)
+        .context("finding running session for workspace")?;
 
         Ok(session)
     }
@@ -443,27 +720,27 @@ impl SessionRepository {
         workspace_path: &str,
     ) -> Result<Option<Session>> {
         let query = format!(
-            "SELECT {} FROM sessions WHERE user_id = ? AND workspace_path = ? AND status = 'stopped' AND container_id IS NOT NULL ORDER BY stopped_at DESC LIMIT 1",
+            "SELECT {} FROM sessions WHERE user_id = $1 AND workspace_path = $2 AND status = 'stopped' AND container_id IS NOT NULL ORDER BY stopped_at DESC LIMIT 1",
             SESSION_COLUMNS
         );
-        let session = sqlx::query_as::<_, Session>(&query)
+        let session = on_pool!(&self.pool, |pool| sqlx::query_as::<_, Session>(&query)
             .bind(user_id)
             .bind(workspace_path)
-            .fetch_optional(&self.pool)
-            .await
-            .context("finding stopped session for workspace")?;
+            .fetch_optional(pool)
+            .await)
+        .context("finding stopped session for workspace")?;
 
         Ok(session)
     }
 
     /// Count running sessions for a user.
     pub async fn count_running_for_user(&self, user_id: &str) -> Result<i64> {
-        let count: (i64,) = sqlx::query_as(
-            "SELECT COUNT(*) FROM sessions WHERE user_id = ? AND status = 'running'",
+        let count: (i64,) = on_pool!(&self.pool, |pool| sqlx::query_as(
+            "SELECT COUNT(*) FROM sessions WHERE user_id = $1 AND status = 'running'",
         )
         .bind(user_id)
-        .fetch_one(&self.pool)
-        .await
+        .fetch_one(pool)
+        .await)
         .context("counting running sessions")?;
 
         Ok(count.0)
@@ -478,16 +755,17 @@ impl SessionRepository {
         agent_count: i64,
     ) -> Result<i64> {
         // Get all port ranges currently in use by active sessions
-        let used_ranges: Vec<(i64, i64, i64, Option<i64>, Option<i64>)> = sqlx::query_as(
-            r#"
+        let used_ranges: Vec<(i64, i64, i64, Option<i64>, Option<i64>)> =
+            on_pool!(&self.pool, |pool| sqlx::query_as(
+                r#"
             SELECT agent_port, fileserver_port, ttyd_port, agent_base_port, max_agents
             FROM sessions
             WHERE status IN ('pending', 'starting', 'running')
             "#,
-        )
-        .fetch_all(&self.pool)
-        .await
-        .context("fetching used ports")?;
+            )
+            .fetch_all(pool)
+            .await)
+            .context("fetching used ports")?;
 
         // We need: agent (reserved), fileserver, ttyd (3 ports) + agent_count ports for sub-agents
         let ports_needed = 3 + agent_count;
@@ -526,4 +804,63 @@ impl SessionRepository {
             }
         }
     }
+
+    /// Store a session's setup, replacing an earlier one.
+    pub async fn save_setup(&self, setup: &SessionSetup) -> Result<()> {
+        let env = serde_json::to_string(&setup.env)?;
+        let mounts = serde_json::to_string(&setup.mounts)?;
+        on_pool!(&self.pool, |pool| sqlx::query(
+            r#"
+            INSERT INTO session_setups (session_id, cloned_from, workspace, provider, model, env, mounts, sandbox_profile)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+            ON CONFLICT (session_id) DO UPDATE SET
+                cloned_from = excluded.cloned_from,
+                workspace = excluded.workspace,
+                provider = excluded.provider,
+                model = excluded.model,
+                env = excluded.env,
+                mounts = excluded.mounts,
+                sandbox_profile = excluded.sandbox_profile
+            "#,
+        )
+        .bind(&setup.session_id)
+        .bind(&setup.cloned_from)
+        .bind(setup.workspace.map(|w| w.as_str()))
+        .bind(&setup.provider)
+        .bind(&setup.model)
+        .bind(&env)
+        .bind(&mounts)
+        .bind(&setup.sandbox_profile)
+        .execute(pool)
+        .await
+        .map(|r| r.rows_affected()))
+        .context("saving session setup")?;
+
+        Ok(())
+    }
+
+    /// Get a session's setup. Sessions created without one have none.
+    pub async fn get_setup(&self, session_id: &str) -> Result<Option<SessionSetup>> {
+        let row = on_pool!(&self.pool, |pool| sqlx::query_as::<_, SessionSetupRow>(
+            "SELECT session_id, cloned_from, workspace, provider, model, env, mounts, \
+             sandbox_profile, created_at FROM session_setups WHERE session_id = $1"
+        )
+        .bind(session_id)
+        .fetch_optional(pool)
+        .await)
+        .context("fetching session setup")?;
+
+        row.map(TryInto::try_into).transpose()
+    }
+
+    /// IDs of the sessions cloned from a session, oldest first.
+    pub async fn list_clones(&self, session_id: &str) -> Result<Vec<String>> {
+        on_pool!(&self.pool, |pool| sqlx::query_scalar(
+            "SELECT session_id FROM session_setups WHERE cloned_from = $1 ORDER BY created_at, session_id"
+        )
+        .bind(session_id)
+        .fetch_all(pool)
+        .await)
+        .context("listing session clones")
+    }
 }
@@ -0,0 +1,468 @@
+//! Live resource usage of running sessions.
+//!
+//! A background task samples every running session: `docker stats` (and
+//! `nvidia-smi` inside containers given GPUs) for container sessions, the
+//! runner's procfs readings of the session's process trees for local ones.
+//! Samples are pushed to the owner as `session.resources` events and the
+//! last few minutes are kept for sparklines. A session keeping the CPU busy
+//! counts as active, so a long build is not stopped as idle.
+
+use std::collections::{HashMap, VecDeque};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+
+/// Longest sampling interval a client can ask for.
+const MAX_INTERVAL_SECS: u64 = 300;
+
+/// A busy session's activity is recorded at most this often.
+const ACTIVITY_TOUCH_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Session resource telemetry configuration.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ResourceTelemetryConfig {
+    /// Sample running sessions and stream `session.resources` events.
+    pub enabled: bool,
+    /// Default time between samples of a session.
+    pub interval_secs: u64,
+    /// Shortest interval a client can switch a session to.
+    pub min_interval_secs: u64,
+    /// Samples kept per session for sparklines.
+    pub history_samples: usize,
+    /// CPU usage (percent of one core) at which a session counts as active
+    /// for the idle timeout. 0 disables.
+    pub active_cpu_percent: f64,
+}
+
+impl Default for ResourceTelemetryConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            interval_secs: 5,
+            min_interval_secs: 1,
+            history_samples: 120,
+            active_cpu_percent: 10.0,
+        }
+    }
+}
+
+/// Resource usage of a session at one point in time.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ResourceSample {
+    /// Unix ms.
+    pub at: i64,
+    /// Percent of one core (400 = four cores busy).
+    pub cpu_percent: f64,
+    pub memory_bytes: u64,
+    /// Memory cap of the session's container.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub memory_limit_bytes: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub processes: Option<u32>,
+    /// Utilization of the container's GPUs (average).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub gpu_percent: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub gpu_memory_bytes: Option<u64>,
+}
+
+/// CPU usage as read from a session.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CpuReading {
+    /// Usage since the previous reading, as the container runtime reports it.
+    Percent(f64),
+    /// Cumulative CPU time; usage is the difference to the previous reading.
+    TimeUs(u64),
+}
+
+/// A raw reading of a session's usage.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ResourceReading {
+    /// Unix ms.
+    pub at: i64,
+    pub cpu: CpuReading,
+    pub memory_bytes: u64,
+    pub memory_limit_bytes: Option<u64>,
+    pub processes: Option<u32>,
+    pub gpu_percent: Option<f64>,
+    pub gpu_memory_bytes: Option<u64>,
+}
+
+/// How a session is sampled.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SamplingSettings {
+    pub interval_secs: u64,
+    /// Push samples as `session.resources` events. Sampling (and the
+    /// history) continues while this is off.
+    pub stream: bool,
+}
+
+/// Change to a session's sampling. Unset fields stay as they are.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct UpdateSamplingRequest {
+    #[serde(default)]
+    pub interval_secs: Option<u64>,
+    #[serde(default)]
+    pub stream: Option<bool>,
+}
+
+/// A session's recent usage.
+#[derive(Debug, Clone, Serialize)]
+pub struct ResourceHistory {
+    pub session_id: String,
+    pub sampling: SamplingSettings,
+    /// Oldest first.
+    pub samples: Vec<ResourceSample>,
+}
+
+/// A reading turned into a sample.
+#[derive(Debug, Clone)]
+pub struct RecordedSample {
+    pub user_id: String,
+    pub sample: ResourceSample,
+    /// Push it to the owner.
+    pub stream: bool,
+    /// Record activity on the session: it is busy and was not touched
+    /// recently.
+    pub touch_activity: bool,
+}
+
+struct TrackedSession {
+    user_id: String,
+    sampling: SamplingSettings,
+    history: VecDeque<ResourceSample>,
+    /// `(at, cpu_time_us)` of the last cumulative reading.
+    last_cpu_time: Option<(i64, u64)>,
+    next_due: Instant,
+    last_touch: Option<Instant>,
+}
+
+/// Sampling state and recent samples of the running sessions.
+pub struct SessionResourceMonitor {
+    config: ResourceTelemetryConfig,
+    sessions: Mutex<HashMap<String, TrackedSession>>,
+}
+
+impl SessionResourceMonitor {
+    pub fn new(config: ResourceTelemetryConfig) -> Self {
+        Self {
+            config,
+            sessions: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn config(&self) -> &ResourceTelemetryConfig {
+        &self.config
+    }
+
+    /// Track the running sessions (`(session_id, user_id)`), forget the
+    /// others, and return the ones due for a sample. Their next sample is
+    /// due an interval later, whether this one succeeds or not.
+    pub fn due(&self, running: &[(String, String)], now: Instant) -> Vec<String> {
+        let mut sessions = self.sessions.lock().unwrap_or_else(|e| e.into_inner());
+        sessions.retain(|id, _| running.iter().any(|(running_id, _)| running_id == id));
+        let mut due = Vec::new();
+        for (session_id, user_id) in running {
+            let tracked = sessions
+                .entry(session_id.clone())
+                .or_insert_with(|| TrackedSession {
+                    user_id: user_id.clone(),
+                    sampling: SamplingSettings {
+                        interval_secs: self.config.interval_secs.max(1),
+                        stream: true,
+                    },
+                    history: VecDeque::new(),
+                    last_cpu_time: None,
+                    next_due: now,
+                    last_touch: None,
+                });
+            if tracked.next_due <= now {
+                tracked.next_due = now + Duration::from_secs(tracked.sampling.interval_secs);
+                due.push(session_id.clone());
+            }
+        }
+        due
+    }
+
+    /// Record a reading of a tracked session. None when the session is no
+    /// longer tracked, or for the first cumulative reading, which only
+    /// serves as the baseline of the next.
+    pub fn record(
+        &self,
+        session_id: &str,
+        reading: ResourceReading,
+        now: Instant,
+    ) -> Option<RecordedSample> {
+        let mut sessions = self.sessions.lock().unwrap_or_else(|e| e.into_inner());
+        let tracked = sessions.get_mut(session_id)?;
+        let cpu_percent = match reading.cpu {
+            CpuReading::Percent(percent) => percent,
+            CpuReading::TimeUs(cpu_time_us) => {
+                let previous = tracked.last_cpu_time.replace((reading.at, cpu_time_us));
+                let (at, before) = previous?;
+                cpu_percent_between(at, before, reading.at, cpu_time_us)?
+            }
+        };
+        let sample = ResourceSample {
+            at: reading.at,
+            cpu_percent,
+            memory_bytes: reading.memory_bytes,
+            memory_limit_bytes: reading.memory_limit_bytes,
+            processes: reading.processes,
+            gpu_percent: reading.gpu_percent,
+            gpu_memory_bytes: reading.gpu_memory_bytes,
+        };
+        tracked.history.push_back(sample.clone());
+        while tracked.history.len() > self.config.history_samples.max(1) {
+            tracked.history.pop_front();
+        }
+
+        let busy = self.config.active_cpu_percent > 0.0
+            && sample.cpu_percent >= self.config.active_cpu_percent;
+        let touch_activity = busy
+            && tracked
+                .last_touch
+                .is_none_or(|at| now.duration_since(at) >= ACTIVITY_TOUCH_INTERVAL);
+        if touch_activity {
+            tracked.last_touch = Some(now);
+        }
+        Some(RecordedSample {
+            user_id: tracked.user_id.clone(),
+            sample,
+            stream: tracked.sampling.stream,
+            touch_activity,
+        })
+    }
+
+    /// Recent samples of a tracked session.
+    pub fn history(&self, session_id: &str) -> Option<ResourceHistory> {
+        let sessions = self.sessions.lock().unwrap_or_else(|e| e.into_inner());
+        let tracked = sessions.get(session_id)?;
+        Some(ResourceHistory {
+            session_id: session_id.to_string(),
+            sampling: tracked.sampling,
+            samples: tracked.history.iter().cloned().collect(),
+        })
+    }
+
+    /// Change how a tracked session is sampled until it stops. The interval
+    /// is clamped to `min_interval_secs..=300`.
+    pub fn set_sampling(
+        &self,
+        session_id: &str,
+        update: &UpdateSamplingRequest,
+        now: Instant,
+    ) -> Option<SamplingSettings> {
+        let mut sessions = self.sessions.lock().unwrap_or_else(|e| e.into_inner());
+        let tracked = sessions.get_mut(session_id)?;
+        if let Some(interval_secs) = update.interval_secs {
+            tracked.sampling.interval_secs =
+                interval_secs.clamp(self.config.min_interval_secs.max(1), MAX_INTERVAL_SECS);
+            tracked.next_due = tracked
+                .next_due
+                .min(now + Duration::from_secs(tracked.sampling.interval_secs));
+        }
+        if let Some(stream) = update.stream {
+            tracked.sampling.stream = stream;
+        }
+        Some(tracked.sampling)
+    }
+}
+
+/// CPU usage between two cumulative readings, in percent of one core. None
+/// when no time passed; a process tree that lost a reaping parent can count
+/// less CPU time than before, which reads as idle.
+fn cpu_percent_between(at_before: i64, before_us: u64, at: i64, now_us: u64) -> Option<f64> {
+    let elapsed_ms = at.checked_sub(at_before).filter(|ms| *ms > 0)?;
+    let used_us = now_us.saturating_sub(before_us);
+    Some(used_us as f64 / (elapsed_ms as f64 * 1000.0) * 100.0)
+}
+
+/// `12.34%` as reported by `docker stats`.
+pub fn parse_percent(value: &str) -> Option<f64> {
+    value.trim().trim_end_matches('%').trim().parse().ok()
+}
+
+/// A size as printed by `docker stats` (`1.5GiB`) or `podman stats`
+/// (`1.61GB`, `512kB`).
+pub fn parse_size(value: &str) -> Option<u64> {
+    let value = value.trim();
+    let split = value
+        .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+        .unwrap_or(value.len());
+    let (number, unit) = value.split_at(split);
+    let number: f64 = number.parse().ok()?;
+    let factor: f64 = match unit.trim() {
+        "" | "B" => 1.0,
+        "kB" | "KB" => 1e3,
+        "MB" => 1e6,
+        "GB" => 1e9,
+        "TB" => 1e12,
+        "KiB" => 1024.0,
+        "MiB" => 1024.0 * 1024.0,
+        "GiB" => 1024.0 * 1024.0 * 1024.0,
+        "TiB" => 1024.0 * 1024.0 * 1024.0 * 1024.0,
+        _ => return None,
+    };
+    Some((number * factor).round() as u64)
+}
+
+/// `(used, limit)` of a `docker stats` memory column (`1.2GiB / 4GiB`).
+pub fn parse_memory_usage(value: &str) -> (Option<u64>, Option<u64>) {
+    match value.split_once('/') {
+        Some((used, limit)) => (parse_size(used), parse_size(limit)),
+        None => (parse_size(value), None),
+    }
+}
+
+/// Command printing GPU utilization and memory, one line per GPU.
+pub const NVIDIA_SMI_QUERY: &[&str] = &[
+    "nvidia-smi",
+    "--query-gpu=utilization.gpu,memory.used",
+    "--format=csv,noheader,nounits",
+];
+
+/// Average utilization and total memory of the GPUs in
+/// [`NVIDIA_SMI_QUERY`] output (MiB). None without any GPU line.
+pub fn parse_gpu_usage(csv: &str) -> Option<(f64, u64)> {
+    let gpus: Vec<(f64, u64)> = csv
+        .lines()
+        .filter_map(|line| {
+            let (utilization, memory) = line.split_once(',')?;
+            Some((
+                utilization.trim().parse().ok()?,
+                memory.trim().parse::<u64>().ok()? * 1024 * 1024,
+            ))
+        })
+        .collect();
+    if gpus.is_empty() {
+        return None;
+    }
+    let utilization = gpus.iter().map(|(u, _)| u).sum::<f64>() / gpus.len() as f64;
+    Some((utilization, gpus.iter().map(|(_, m)| m).sum()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn reading(at: i64, cpu: CpuReading) -> ResourceReading {
+        ResourceReading {
+            at,
+            cpu,
+            memory_bytes: 1024,
+            memory_limit_bytes: None,
+            processes: Some(3),
+            gpu_percent: None,
+            gpu_memory_bytes: None,
+        }
+    }
+
+    #[test]
+    fn parses_container_stats() {
+        assert_eq!(parse_percent("12.5%"), Some(12.5));
+        assert_eq!(parse_percent("--"), None);
+        assert_eq!(
+            parse_memory_usage("1.5GiB / 4GiB"),
+            (Some(1_610_612_736), Some(4_294_967_296))
+        );
+        assert_eq!(
+            parse_memory_usage("512kB / 1.61GB"),
+            (Some(512_000), Some(1_610_000_000))
+        );
+        assert_eq!(parse_size("12 parsecs"), None);
+        assert_eq!(
+            parse_gpu_usage("30, 1024\n50, 2048\n"),
+            Some((40.0, 3072 * 1024 * 1024))
+        );
+        assert_eq!(parse_gpu_usage("No devices were found\n"), None);
+    }
+
+    #[test]
+    fn cumulative_cpu_time_becomes_percent() {
+        let monitor = SessionResourceMonitor::new(ResourceTelemetryConfig {
+            history_samples: 2,
+            ..Default::default()
+        });
+        let now = Instant::now();
+        let running = [("s1".to_string(), "u1".to_string())];
+        assert_eq!(monitor.due(&running, now), vec!["s1"]);
+
+        // The first reading is the baseline.
+        assert!(
+            monitor
+                .record("s1", reading(1_000, CpuReading::TimeUs(0)), now)
+                .is_none()
+        );
+        assert!(monitor.due(&running, now).is_empty());
+        // 3s of CPU time in 2s: one and a half cores.
+        let recorded = monitor
+            .record("s1", reading(3_000, CpuReading::TimeUs(3_000_000)), now)
+            .unwrap();
+        assert_eq!(recorded.sample.cpu_percent, 150.0);
+        assert_eq!(recorded.user_id, "u1");
+        assert!(recorded.stream);
+        assert!(recorded.touch_activity);
+
+        // Busy again within the minute: no second touch.
+        let recorded = monitor
+            .record("s1", reading(5_000, CpuReading::TimeUs(6_000_000)), now)
+            .unwrap();
+        assert!(!recorded.touch_activity);
+        let recorded = monitor
+            .record("s1", reading(7_000, CpuReading::Percent(2.0)), now)
+            .unwrap();
+        assert!(!recorded.touch_activity);
+
+        let history = monitor.history("s1").unwrap();
+        assert_eq!(history.samples.len(), 2);
+        assert_eq!(history.samples[1].cpu_percent, 2.0);
+
+        // Stopped sessions are forgotten.
+        monitor.due(&[], now);
+        assert!(monitor.history("s1").is_none());
+    }
+
+    #[test]
+    fn sampling_can_be_changed_per_session() {
+        let monitor = SessionResourceMonitor::new(ResourceTelemetryConfig::default());
+        let now = Instant::now();
+        let running = [("s1".to_string(), "u1".to_string())];
+        monitor.due(&running, now);
+        monitor.record("s1", reading(1_000, CpuReading::Percent(0.0)), now);
+        assert!(
+            monitor
+                .due(&running, now + Duration::from_secs(2))
+                .is_empty()
+        );
+
+        let sampling = monitor
+            .set_sampling(
+                "s1",
+                &UpdateSamplingRequest {
+                    interval_secs: Some(0),
+                    stream: Some(false),
+                },
+                now,
+            )
+            .unwrap();
+        assert_eq!(
+            sampling,
+            SamplingSettings {
+                interval_secs: 1,
+                stream: false
+            }
+        );
+        assert_eq!(
+            monitor.due(&running, now + Duration::from_secs(2)),
+            vec!["s1"]
+        );
+        assert!(
+            monitor
+                .set_sampling("other", &UpdateSamplingRequest::default(), now)
+                .is_none()
+        );
+    }
+}
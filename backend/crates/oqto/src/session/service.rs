@@ -7,14 +7,16 @@
 //! The service manages session lifecycles and runtime orchestration.
 
 use std::collections::HashSet;
+use std::net::IpAddr;
 use std::path::PathBuf;
-use std::sync::Arc;
+use std::sync::{Arc, RwLock};
 
 use anyhow::{Context, Result};
 use async_trait::async_trait;
 use chrono::Utc;
 use log::{debug, error, info, warn};
 use serde::Serialize;
+use tokio::sync::broadcast;
 use uuid::Uuid;
 
 use crate::agent_browser::{AgentBrowserConfig, AgentBrowserManager, browser_session_name};
@@ -39,14 +41,31 @@ impl BrowserAction {
         }
     }
 }
-use crate::container::{ContainerConfig, ContainerRuntimeApi, ContainerStats};
-use crate::eavs::{CreateKeyRequest, EavsApi, KeyPermissions};
-use crate::local::{LocalRuntime, LocalRuntimeConfig, UserMmryManager};
+use crate::container::{
+    ContainerConfig, ContainerRuntimeApi, ContainerStats, ImageBuildSpec, ResourceLimits,
+};
+use crate::eavs::{CreateKeyRequest, EavsApi, KeyPermissions, TrafficLane};
+use crate::local::{LocalRuntime, LocalRuntimeConfig, ProcessHandle, UserMmryManager};
+use crate::registration::RegistrationService;
+use crate::secrets::{CONTAINER_SECRETS_DIR, SecretStore};
 use crate::wordlist;
+use crate::workspace::config::WorkspaceConfig;
 use oqto_runner::client::RunnerClient;
-
-use super::models::{CreateSessionRequest, RuntimeMode, Session, SessionStatus};
+use oqto_runner::protocol::{
+    PiCreateSessionRequest, PiSessionConfig, PiSessionInfo, PiSessionState, SessionSecrets,
+};
+use oqto_runner::session_secrets::SECRETS_DIR_ENV;
+
+use super::egress::{BlockedEvent, ContainerEgress, EgressRules};
+use super::exit_info::{ExitEvidence, ExitInfo};
+use super::models::{
+    CloneSessionRequest, CloneWorkspace, CreateSessionRequest, HibernatedAgent, Hibernation,
+    IdleAction, RuntimeMode, Session, SessionLifecycleEvent, SessionMount, SessionSetup,
+    SessionStatus, UserResourceLimits,
+};
+use super::port_conflict::{self, PortConflict, PortConflictEvent};
 use super::repository::SessionRepository;
+use super::resources::{self, CpuReading, ResourceReading};
 use super::workspace_locations::WorkspaceLocationRepository;
 
 /// Prefix used for container names managed by this orchestrator.
@@ -58,6 +77,22 @@ const DEFAULT_IMAGE: &str = "oqto:latest";
 /// Default base port.
 const DEFAULT_BASE_PORT: i64 = 41820;
 
+/// Lines of container output searched for known failures when it exits.
+const EXIT_LOG_TAIL_LINES: u32 = 50;
+
+/// Lifetime of fileserver capability tokens minted for proxied requests.
+const FILESERVER_TOKEN_TTL: std::time::Duration = std::time::Duration::from_secs(60);
+
+/// Where a container's fileserver finds its token secret, mounted read-only
+/// from the host so it can be rotated without recreating the container.
+const CONTAINER_FILESERVER_DIR: &str = "/run/oqto/fileserver";
+
+/// Name of the token secret file in [`CONTAINER_FILESERVER_DIR`].
+const FILESERVER_TOKEN_FILE: &str = "token-secret";
+
+/// Size of the port conflict broadcast channel.
+const PORT_CONFLICT_BUFFER_SIZE: usize = 64;
+
 #[async_trait]
 trait SessionReadiness: Send + Sync {
     async fn wait_for_session_services(&self, fileserver_port: u16, ttyd_port: u16) -> Result<()>;
@@ -74,7 +109,8 @@ impl SessionReadiness for HttpSessionReadiness {
             .build()
             .context("building readiness HTTP client")?;
 
-        let fileserver_url = format!("http://localhost:{}/tree?path=.", fileserver_port);
+        // `/health` is the one fileserver route that needs no capability token.
+        let fileserver_url = format!("http://localhost:{}/health", fileserver_port);
         let ttyd_url = format!("http://localhost:{}/", ttyd_port);
 
         let start = tokio::time::Instant::now();
@@ -129,6 +165,8 @@ pub struct SessionServiceConfig {
     pub user_data_path: String,
     /// Path to skeleton directory to copy into new user homes. If None, empty dirs are created.
     pub skel_path: Option<String>,
+    /// Default container resource limits; admins can override them per user.
+    pub resource_limits: ResourceLimits,
     /// Default budget limit per session in USD.
     pub default_session_budget_usd: Option<f64>,
     /// Default rate limit per session (requests per minute).
@@ -151,6 +189,8 @@ pub struct SessionServiceConfig {
     pub max_concurrent_sessions: i64,
     /// Idle timeout in minutes before stopping a session.
     pub idle_timeout_minutes: i64,
+    /// What happens to sessions past the idle timeout.
+    pub idle_action: IdleAction,
     /// Idle cleanup check interval in seconds.
     pub idle_check_interval_seconds: u64,
     /// Whether Pi (Main Chat AI) is enabled in container mode.
@@ -178,6 +218,7 @@ impl Default for SessionServiceConfig {
             base_port: DEFAULT_BASE_PORT,
             user_data_path: "./data".to_string(),
             skel_path: None,
+            resource_limits: ResourceLimits::default(),
             default_session_budget_usd: Some(10.0),
             default_session_rpm: Some(60),
             eavs_container_url: None,
@@ -188,6 +229,7 @@ impl Default for SessionServiceConfig {
             mmry_container_url: None,
             max_concurrent_sessions: SessionService::DEFAULT_MAX_CONCURRENT_SESSIONS,
             idle_timeout_minutes: SessionService::DEFAULT_IDLE_TIMEOUT_MINUTES,
+            idle_action: IdleAction::Stop,
             idle_check_interval_seconds: 5 * 60,
             pi_bridge_enabled: false,
             pi_provider: None,
@@ -199,6 +241,28 @@ impl Default for SessionServiceConfig {
     }
 }
 
+/// Session settings a config reload can change while the server runs.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SessionLimits {
+    pub max_concurrent_sessions: i64,
+    pub idle_timeout_minutes: i64,
+    pub idle_action: IdleAction,
+    pub default_session_budget_usd: Option<f64>,
+    pub default_session_rpm: Option<u32>,
+}
+
+impl SessionLimits {
+    fn from_config(config: &SessionServiceConfig) -> Self {
+        Self {
+            max_concurrent_sessions: config.max_concurrent_sessions,
+            idle_timeout_minutes: config.idle_timeout_minutes,
+            idle_action: config.idle_action,
+            default_session_budget_usd: config.default_session_budget_usd,
+            default_session_rpm: config.default_session_rpm,
+        }
+    }
+}
+
 /// A view of `SessionService` scoped to a single user.
 #[derive(Clone, Copy)]
 pub struct UserSessionService<'a> {
@@ -227,6 +291,52 @@ impl<'a> UserSessionService<'a> {
             .await
     }
 
+    /// Create a session with a setup (model, extra environment, mounts),
+    /// e.g. one configured as a draft.
+    pub async fn create_configured_session(
+        &self,
+        request: CreateSessionRequest,
+        setup: SessionSetup,
+    ) -> Result<Session> {
+        self.svc
+            .create_configured_session_for_user(self.user_id, request, setup)
+            .await
+    }
+
+    /// Check the mounts of a setup: their sources must be existing
+    /// directories in the user's workspace roots.
+    pub async fn validate_mounts(&self, mounts: &[SessionMount]) -> Result<()> {
+        self.svc
+            .resolve_mounts(self.user_id, mounts)
+            .await
+            .map(|_| ())
+    }
+
+    /// Create a session with the setup of one of the user's sessions.
+    pub async fn clone_session(
+        &self,
+        source_id: &str,
+        request: CloneSessionRequest,
+    ) -> Result<Session> {
+        self.svc
+            .clone_session_for_user(self.user_id, source_id, request)
+            .await
+    }
+
+    /// Setup of one of the user's sessions. None when the session is not
+    /// the user's; sessions created without a setup get an empty one.
+    pub async fn get_session_setup(&self, session_id: &str) -> Result<Option<SessionSetup>> {
+        let Some(session) = self.get_session(session_id).await? else {
+            return Ok(None);
+        };
+        Ok(Some(self.svc.session_setup(&session).await?))
+    }
+
+    /// IDs of the sessions cloned from a session.
+    pub async fn list_session_clones(&self, session_id: &str) -> Result<Vec<String>> {
+        self.svc.repo.list_clones(session_id).await
+    }
+
     pub async fn get_or_create_session(&self, request: CreateSessionRequest) -> Result<Session> {
         self.svc
             .get_or_create_session_for_user(self.user_id, request)
@@ -342,7 +452,17 @@ pub struct SessionService {
     readiness: Arc<dyn SessionReadiness>,
     agent_browser: AgentBrowserManager,
     config: SessionServiceConfig,
+    /// Shared by all clones of the service, so reloads reach the idle sweep.
+    limits: Arc<RwLock<SessionLimits>>,
     user_mmry: Option<Arc<UserMmryManager>>,
+    /// Open registrations, whose unverified users are quarantined.
+    registration: Option<Arc<RegistrationService>>,
+    /// Sessions moved to other ports after a conflict on start.
+    port_conflicts: broadcast::Sender<PortConflictEvent>,
+    /// Egress rules attached to session containers (container mode).
+    egress: Option<ContainerEgress>,
+    /// The users' secrets, injected into sessions when they start.
+    secrets: Option<Arc<SecretStore>>,
 }
 
 impl SessionService {
@@ -371,8 +491,13 @@ impl SessionService {
             eavs: None,
             readiness: Arc::new(HttpSessionReadiness),
             agent_browser: AgentBrowserManager::new(config.agent_browser.clone()),
+            limits: Arc::new(RwLock::new(SessionLimits::from_config(&config))),
             config,
             user_mmry: None,
+            registration: None,
+            port_conflicts: broadcast::channel(PORT_CONFLICT_BUFFER_SIZE).0,
+            egress: None,
+            secrets: None,
         }
     }
 
@@ -393,8 +518,13 @@ impl SessionService {
             eavs: Some(eavs),
             readiness: Arc::new(HttpSessionReadiness),
             agent_browser: AgentBrowserManager::new(config.agent_browser.clone()),
+            limits: Arc::new(RwLock::new(SessionLimits::from_config(&config))),
             config,
             user_mmry: None,
+            registration: None,
+            port_conflicts: broadcast::channel(PORT_CONFLICT_BUFFER_SIZE).0,
+            egress: None,
+            secrets: None,
         }
     }
 
@@ -418,8 +548,13 @@ impl SessionService {
             eavs: None,
             readiness: Arc::new(HttpSessionReadiness),
             agent_browser: AgentBrowserManager::new(config.agent_browser.clone()),
+            limits: Arc::new(RwLock::new(SessionLimits::from_config(&config))),
             config,
             user_mmry: None,
+            registration: None,
+            port_conflicts: broadcast::channel(PORT_CONFLICT_BUFFER_SIZE).0,
+            egress: None,
+            secrets: None,
         }
     }
 
@@ -441,8 +576,29 @@ impl SessionService {
             eavs: Some(eavs),
             readiness: Arc::new(HttpSessionReadiness),
             agent_browser: AgentBrowserManager::new(config.agent_browser.clone()),
+            limits: Arc::new(RwLock::new(SessionLimits::from_config(&config))),
             config,
             user_mmry: None,
+            registration: None,
+            port_conflicts: broadcast::channel(PORT_CONFLICT_BUFFER_SIZE).0,
+            egress: None,
+            secrets: None,
+        }
+    }
+
+    /// Current session limits.
+    pub fn limits(&self) -> SessionLimits {
+        self.limits
+            .read()
+            .map(|limits| *limits)
+            .unwrap_or_else(|_| SessionLimits::from_config(&self.config))
+    }
+
+    /// Replace the session limits. Running sessions are not touched; the new
+    /// limits apply to the next session start, idle sweep and EAVS key.
+    pub fn set_limits(&self, limits: SessionLimits) {
+        if let Ok(mut current) = self.limits.write() {
+            *current = limits;
         }
     }
 
@@ -452,6 +608,42 @@ impl SessionService {
         self
     }
 
+    /// Hold users of open registrations to the quarantine quota until they
+    /// verify their email address.
+    pub fn with_registration(mut self, registration: Arc<RegistrationService>) -> Self {
+        self.registration = Some(registration);
+        self
+    }
+
+    /// Enforce the egress rules of the sessions' sandbox profiles on their
+    /// containers (container mode).
+    pub fn with_egress(mut self, egress: ContainerEgress) -> Self {
+        self.egress = Some(egress);
+        self
+    }
+
+    /// Inject the users' secrets into their sessions.
+    pub fn with_secrets(mut self, secrets: Arc<SecretStore>) -> Self {
+        self.secrets = Some(secrets);
+        self
+    }
+
+    /// Subscribe to sessions moved to other ports after a port conflict.
+    pub fn subscribe_port_conflicts(&self) -> broadcast::Receiver<PortConflictEvent> {
+        self.port_conflicts.subscribe()
+    }
+
+    /// Subscribe to connections the egress policy blocked, when one is
+    /// enforced.
+    pub fn subscribe_blocked_connections(&self) -> Option<broadcast::Receiver<BlockedEvent>> {
+        self.egress.as_ref().map(ContainerEgress::subscribe)
+    }
+
+    /// Subscribe to sessions being created, started, stopped and failing.
+    pub fn subscribe_lifecycle(&self) -> broadcast::Receiver<SessionLifecycleEvent> {
+        self.repo.subscribe_lifecycle()
+    }
+
     /// Get runner client for a user.
     ///
     /// In single-user mode, returns the shared runner.
@@ -583,6 +775,149 @@ impl SessionService {
         self.container_runtime.as_ref()
     }
 
+    /// Egress rules for the container of a session, from the sandbox
+    /// profile in its setup. None when there are no rules to enforce. Fails
+    /// when the setup or the sandbox config cannot be read, so the session
+    /// does not start without its rules.
+    async fn egress_rules(&self, session_id: &str) -> Result<Option<EgressRules>> {
+        let Some(egress) = &self.egress else {
+            return Ok(None);
+        };
+        let profile = self
+            .repo
+            .get_setup(session_id)
+            .await
+            .context("reading the session's sandbox profile")?
+            .and_then(|setup| setup.sandbox_profile);
+        egress
+            .rules_for(profile.as_deref())
+            .context("resolving the session's egress rules")
+    }
+
+    /// Start a created or stopped container behind egress rules: the runtime
+    /// holds it with its namespaces set up, the rules are attached, and only
+    /// then does its entrypoint run.
+    async fn start_container_behind(
+        &self,
+        runtime: &dyn ContainerRuntimeApi,
+        session_id: &str,
+        user_id: &str,
+        container_id: &str,
+        rules: &EgressRules,
+    ) -> Result<()> {
+        let egress = self
+            .egress
+            .as_ref()
+            .context("egress rules are not enforced")?;
+        runtime
+            .hold_container(container_id)
+            .await
+            .context("holding the container for its egress rules")?;
+        let pid = runtime
+            .container_pid(container_id)
+            .await?
+            .context("the container runtime does not report the container's pid")?;
+        egress.attach(session_id, user_id, pid, rules).await?;
+        if let Err(e) = runtime.start_container(container_id).await {
+            egress.release(session_id);
+            return Err(e.into());
+        }
+        Ok(())
+    }
+
+    /// Start a stopped container of a session, behind its egress rules when
+    /// it has any, with a fresh fileserver token secret.
+    async fn start_stopped_container(
+        &self,
+        runtime: &dyn ContainerRuntimeApi,
+        session_id: &str,
+        user_id: &str,
+        container_id: &str,
+    ) -> Result<()> {
+        self.rewrite_fileserver_token_file(session_id).await?;
+        match self.egress_rules(session_id).await? {
+            Some(rules) => {
+                self.start_container_behind(runtime, session_id, user_id, container_id, &rules)
+                    .await
+            }
+            None => Ok(runtime.start_container(container_id).await?),
+        }
+    }
+
+    /// Start the existing container of a session, from its checkpoint when
+    /// hibernated. A container with egress rules starts afresh behind them:
+    /// restored processes would run before the rules are attached. Either
+    /// way its fileserver gets a fresh token secret.
+    async fn resume_container(
+        &self,
+        runtime: &dyn ContainerRuntimeApi,
+        session: &Session,
+    ) -> Result<()> {
+        self.rewrite_fileserver_token_file(&session.id).await?;
+        let container_id = session.container_id.as_deref().unwrap_or_default();
+        match self.egress_rules(&session.id).await? {
+            Some(rules) => {
+                self.start_container_behind(
+                    runtime,
+                    &session.id,
+                    &session.user_id,
+                    container_id,
+                    &rules,
+                )
+                .await
+            }
+            None => Ok(start_or_restore_container(runtime, session).await?),
+        }
+    }
+
+    /// The secrets of a session's owner that apply to it. When they cannot
+    /// be read, that is logged and the session starts without them.
+    async fn session_secrets(
+        &self,
+        session_id: &str,
+        user_id: &str,
+        workspace_path: &str,
+    ) -> SessionSecrets {
+        let Some(store) = &self.secrets else {
+            return SessionSecrets::default();
+        };
+        match store
+            .for_session(user_id, std::path::Path::new(workspace_path))
+            .await
+        {
+            Ok(secrets) => secrets,
+            Err(e) => {
+                warn!("Starting session {} without secrets: {:?}", session_id, e);
+                SessionSecrets::default()
+            }
+        }
+    }
+
+    /// Write the secret files mounted into a session's container again
+    /// before it starts; they are removed whenever the container stops.
+    async fn write_container_secrets(
+        &self,
+        session_id: &str,
+        user_id: &str,
+        workspace_path: &str,
+    ) -> Result<()> {
+        let Some(store) = &self.secrets else {
+            return Ok(());
+        };
+        let secrets = self
+            .session_secrets(session_id, user_id, workspace_path)
+            .await;
+        store.write_container_files(session_id, &secrets.files)?;
+        Ok(())
+    }
+
+    /// Remove the secret files of a container session.
+    fn remove_container_secrets(&self, session_id: &str) {
+        if let Some(store) = &self.secrets {
+            store.remove_container_files(session_id);
+        }
+    }
+
     /// Get the local runtime (if available).
     fn local_runtime(&self) -> Option<&Arc<LocalRuntime>> {
         self.local_runtime.as_ref()
@@ -621,15 +956,20 @@ impl SessionService {
             .map(Some)
     }
 
-    /// Navigate the agent-browser to a URL, launching the daemon if needed.
-    /// Optionally sets the viewport size.
+    /// Navigate the agent-browser to a URL, launching the daemon (with
+    /// `user_id`'s persistent profile) if needed. Optionally sets the
+    /// viewport size.
     pub async fn navigate_browser(
         &self,
         session_id: &str,
+        user_id: &str,
         url: &str,
         viewport_width: Option<u32>,
         viewport_height: Option<u32>,
     ) -> Result<()> {
+        self.agent_browser
+            .ensure_session(session_id, Some(user_id))
+            .await?;
         self.agent_browser.navigate_to(session_id, url).await?;
         if let (Some(w), Some(h)) = (viewport_width, viewport_height) {
             self.agent_browser.set_viewport(session_id, w, h).await?;
@@ -771,6 +1111,10 @@ impl SessionService {
     /// Maximum number of retries for port allocation conflicts.
     const MAX_PORT_ALLOCATION_RETRIES: u32 = 5;
 
+    /// Maximum number of moves to another port range when a session's ports
+    /// turn out to be taken on start.
+    const MAX_PORT_CONFLICT_RETRIES: u32 = 3;
+
     /// Get or create a session for a user.
     ///
     /// This method:
@@ -873,35 +1217,212 @@ impl SessionService {
         user_id: &str,
         request: CreateSessionRequest,
     ) -> Result<Session> {
-        self.create_session_with_readiness(user_id, request).await
+        self.create_session_with_readiness(user_id, request, None)
+            .await
+    }
+
+    async fn create_configured_session_for_user(
+        &self,
+        user_id: &str,
+        request: CreateSessionRequest,
+        mut setup: SessionSetup,
+    ) -> Result<Session> {
+        validate_setup_env(&setup.env).map_err(anyhow::Error::msg)?;
+        setup.mounts = self.resolve_mounts(user_id, &setup.mounts).await?;
+        self.create_session_with_readiness(user_id, request, Some(setup))
+            .await
+    }
+
+    /// Mounts with their sources resolved against the user's workspace
+    /// roots.
+    async fn resolve_mounts(
+        &self,
+        user_id: &str,
+        mounts: &[SessionMount],
+    ) -> Result<Vec<SessionMount>> {
+        validate_setup_mounts(mounts).map_err(anyhow::Error::msg)?;
+        if !mounts.is_empty() && !self.supports_mounts() {
+            anyhow::bail!("mounts are only supported for container sessions");
+        }
+        let mut resolved = Vec::with_capacity(mounts.len());
+        for mount in mounts {
+            let source = self.resolve_workspace_path(user_id, &mount.source).await?;
+            if !source.is_dir() {
+                anyhow::bail!("mount source {} is not a directory", source.display());
+            }
+            resolved.push(SessionMount {
+                source: source.to_string_lossy().to_string(),
+                ..mount.clone()
+            });
+        }
+        Ok(resolved)
+    }
+
+    /// Check that a session can select the sandbox profile `name`: it must
+    /// exist, and its rules are only enforced on containers.
+    fn check_sandbox_profile(&self, name: &str) -> Result<()> {
+        let egress = self
+            .egress
+            .as_ref()
+            .context("sandbox profiles are only supported for container sessions")?;
+        egress.rules_for(Some(name))?;
+        Ok(())
+    }
+
+    /// Whether sessions can have extra mounts (container mode only).
+    pub fn supports_mounts(&self) -> bool {
+        self.config.runtime_mode == RuntimeMode::Container
+    }
+
+    /// Create a session with the image, agent, model and environment of
+    /// `source_id`, overridden by the request, and record it as a clone.
+    async fn clone_session_for_user(
+        &self,
+        user_id: &str,
+        source_id: &str,
+        request: CloneSessionRequest,
+    ) -> Result<Session> {
+        validate_setup_env(&request.env).map_err(anyhow::Error::msg)?;
+        let source = self
+            .get_session_for_user(user_id, source_id)
+            .await?
+            .with_context(|| format!("session {} not found", source_id))?;
+        let source_setup = self.session_setup(&source).await?;
+
+        let (workspace_path, created_dir) =
+            self.clone_workspace(&source, request.workspace).await?;
+        let mut env = source_setup.env;
+        env.extend(request.env);
+        let setup = SessionSetup {
+            session_id: String::new(),
+            cloned_from: Some(source.id.clone()),
+            workspace: Some(request.workspace),
+            provider: request.provider.or(source_setup.provider),
+            model: request.model.or(source_setup.model),
+            env,
+            mounts: source_setup.mounts,
+            sandbox_profile: source_setup.sandbox_profile,
+            created_at: String::new(),
+        };
+        // The source workspace was checked when the source was created, and
+        // new ones are its siblings.
+        let result = self
+            .create_session_in(
+                user_id,
+                &workspace_path,
+                request.image.unwrap_or(source.image),
+                request.agent.or(source.agent),
+                Some(setup),
+            )
+            .await;
+        if let (Err(_), Some(dir)) = (&result, created_dir)
+            && let Err(e) = std::fs::remove_dir_all(&dir)
+        {
+            warn!(
+                "Failed to remove workspace {:?} of failed clone: {:?}",
+                dir, e
+            );
+        }
+        let session = result?;
+        info!(
+            "Cloned session {} from {} ({} workspace)",
+            session.id,
+            source.id,
+            request.workspace.as_str()
+        );
+        Ok(session)
+    }
+
+    /// Workspace directory for a clone of `source`, and the directory
+    /// created for it (None when the workspace is shared).
+    async fn clone_workspace(
+        &self,
+        source: &Session,
+        mode: CloneWorkspace,
+    ) -> Result<(String, Option<PathBuf>)> {
+        if mode == CloneWorkspace::Shared {
+            return Ok((source.workspace_path.clone(), None));
+        }
+
+        let src = PathBuf::from(&source.workspace_path);
+        let name = src
+            .file_name()
+            .context("source workspace has no directory name")?
+            .to_string_lossy()
+            .to_string();
+        let dst = src.with_file_name(format!(
+            "{}-clone-{}",
+            name,
+            &Uuid::new_v4().to_string()[..8]
+        ));
+        let home_layout = self.config.runtime_mode == RuntimeMode::Container;
+
+        let target = dst.clone();
+        tokio::task::spawn_blocking(move || match mode {
+            CloneWorkspace::Copy => copy_dir_recursive(&src, &target),
+            CloneWorkspace::Clean if home_layout => create_empty_home_dirs(&target),
+            _ => std::fs::create_dir_all(&target).map_err(Into::into),
+        })
+        .await
+        .context("preparing clone workspace")?
+        .with_context(|| format!("preparing clone workspace {:?}", dst))?;
+
+        Ok((dst.to_string_lossy().to_string(), Some(dst)))
+    }
+
+    /// Stored setup of a session, with the configured model filled in for
+    /// sessions that have none.
+    async fn session_setup(&self, session: &Session) -> Result<SessionSetup> {
+        let mut setup = self
+            .repo
+            .get_setup(&session.id)
+            .await?
+            .unwrap_or_else(|| SessionSetup {
+                session_id: session.id.clone(),
+                created_at: session.created_at.clone(),
+                ..Default::default()
+            });
+        if setup.provider.is_none() {
+            setup.provider = self.config.pi_provider.clone();
+        }
+        if setup.model.is_none() {
+            setup.model = self.config.pi_model.clone();
+        }
+        Ok(setup)
+    }
+
+    /// Setup to start a session with. A setup that cannot be read is logged
+    /// and ignored, so the session still starts.
+    async fn startup_setup(&self, session_id: &str) -> SessionSetup {
+        match self.repo.get_setup(session_id).await {
+            Ok(setup) => setup.unwrap_or_default(),
+            Err(e) => {
+                warn!("Ignoring setup of session {}: {:?}", session_id, e);
+                SessionSetup::default()
+            }
+        }
     }
 
     async fn create_session_with_readiness(
         &self,
         user_id: &str,
         request: CreateSessionRequest,
+        setup: Option<SessionSetup>,
     ) -> Result<Session> {
+        let setup = match request.sandbox_profile {
+            Some(profile) => {
+                self.check_sandbox_profile(&profile)?;
+                Some(SessionSetup {
+                    sandbox_profile: Some(profile),
+                    ..setup.unwrap_or_default()
+                })
+            }
+            None => setup,
+        };
         let image = request
             .image
             .unwrap_or_else(|| self.config.default_image.clone());
 
-        // Get current image digest for tracking upgrades (best-effort, container mode only).
-        let image_digest = if self.config.runtime_mode == RuntimeMode::Container {
-            if let Some(runtime) = self.container_runtime() {
-                match runtime.get_image_digest(&image).await {
-                    Ok(digest) => digest,
-                    Err(e) => {
-                        warn!("Failed to get image digest for {}: {:?}", image, e);
-                        None
-                    }
-                }
-            } else {
-                None
-            }
-        } else {
-            None // Local mode doesn't track image digests
-        };
-
         // Determine user home path - either provided or create per-user home directory.
         let user_home_path = if let Some(path) = request.workspace_path {
             self.resolve_workspace_path(user_id, &path)
@@ -964,17 +1485,55 @@ impl SessionService {
         };
 
         // Use agent from request (LocalRuntime will apply default_agent if None)
-        let agent = request.agent.clone();
+        self.create_session_in(user_id, &user_home_path, image, request.agent, setup)
+            .await
+    }
+
+    /// Create and start a session in an already resolved workspace.
+    async fn create_session_in(
+        &self,
+        user_id: &str,
+        user_home_path: &str,
+        image: String,
+        agent: Option<String>,
+        setup: Option<SessionSetup>,
+    ) -> Result<Session> {
+        self.enforce_quarantine_cap(user_id).await?;
+
+        // Get current image digest for tracking upgrades (best-effort, container mode only).
+        let image_digest = if self.config.runtime_mode == RuntimeMode::Container {
+            if let Some(runtime) = self.container_runtime() {
+                match runtime.get_image_digest(&image).await {
+                    Ok(digest) => digest,
+                    Err(e) => {
+                        warn!("Failed to get image digest for {}: {:?}", image, e);
+                        None
+                    }
+                }
+            } else {
+                None
+            }
+        } else {
+            None // Local mode doesn't track image digests
+        };
+
+        let resource_limits = if self.config.runtime_mode == RuntimeMode::Container {
+            Some(self.effective_resource_limits(user_id).await?)
+        } else {
+            None
+        };
 
         let mut last_error = None;
         for attempt in 0..Self::MAX_PORT_ALLOCATION_RETRIES {
             match self
                 .try_create_session(
-                    &user_home_path,
+                    user_home_path,
                     &image,
                     image_digest.as_deref(),
                     agent.as_deref(),
                     user_id,
+                    setup.as_ref(),
+                    resource_limits.as_ref(),
                     attempt,
                 )
                 .await
@@ -1090,6 +1649,8 @@ impl SessionService {
         image_digest: Option<&str>,
         agent: Option<&str>,
         user_id: &str,
+        setup: Option<&SessionSetup>,
+        resource_limits: Option<&ResourceLimits>,
         attempt: u32,
     ) -> Result<Session> {
         let session_id = Uuid::new_v4().to_string();
@@ -1146,7 +1707,7 @@ impl SessionService {
         };
 
         let now = Utc::now().to_rfc3339();
-        let session = Session {
+        let mut session = Session {
             id: session_id.clone(),
             readable_id: Some(wordlist::readable_id_from_session_id(&session_id)),
             container_id: None,
@@ -1173,6 +1734,9 @@ impl SessionService {
             stopped_at: None,
             last_activity_at: Some(now), // Initialize with creation time
             error_message: None,
+            exit_info: None,
+            resource_limits: resource_limits.cloned(),
+            hibernation: None,
         };
 
         // Persist the session. This will fail with a unique constraint violation if another
@@ -1184,9 +1748,21 @@ impl SessionService {
             session_id, agent_port, fileserver_port, ttyd_port
         );
 
+        // Saved before starting, which reads it.
+        if let Some(setup) = setup {
+            let setup = SessionSetup {
+                session_id: session_id.clone(),
+                ..setup.clone()
+            };
+            if let Err(e) = self.repo.save_setup(&setup).await {
+                let _ = self.repo.mark_failed(&session.id, &e.to_string()).await;
+                return Err(e);
+            }
+        }
+
         // Start the container synchronously so callers can reliably know whether startup succeeded.
         if let Err(e) = self
-            .start_container(&session, eavs_virtual_key.as_deref())
+            .start_with_port_recovery(&mut session, eavs_virtual_key.as_deref())
             .await
         {
             error!(
@@ -1216,11 +1792,12 @@ impl SessionService {
         let eavs = self.eavs.as_ref().context("EAVS client not configured")?;
 
         // Build permissions based on config
+        let limits = self.limits();
         let mut permissions = KeyPermissions::default();
-        if let Some(budget) = self.config.default_session_budget_usd {
+        if let Some(budget) = limits.default_session_budget_usd {
             permissions.max_budget_usd = Some(budget);
         }
-        if let Some(rpm) = self.config.default_session_rpm {
+        if let Some(rpm) = limits.default_session_rpm {
             permissions.rpm_limit = Some(rpm);
         }
 
@@ -1229,7 +1806,8 @@ impl SessionService {
             .metadata(serde_json::json!({
                 "session_id": session_id,
                 "created_by": "oqto"
-            }));
+            }))
+            .lane(TrafficLane::Interactive);
 
         let response = eavs.create_key(request).await?;
 
@@ -1262,29 +1840,155 @@ impl SessionService {
         result
     }
 
-    async fn start_agent_browser_daemon(&self, session: &Session) {
-        let browser_session_id = browser_session_name(&session.id);
-        if let Err(err) = self.agent_browser.ensure_session(&browser_session_id).await {
-            warn!(
-                "Failed to start agent-browser daemon for session {} (browser session {}): {}",
-                session.id, browser_session_id, err
-            );
-        }
-    }
-
-    async fn stop_agent_browser_daemon(&self, session_id: &str) {
-        let browser_session_id = browser_session_name(session_id);
-        if let Err(err) = self.agent_browser.stop_session(&browser_session_id).await {
-            warn!(
-                "Failed to stop agent-browser daemon for session {} (browser session {}): {}",
-                session_id, browser_session_id, err
-            );
-        }
-    }
-
-    /// Internal port base for sub-agents inside the container.
-    const INTERNAL_AGENT_BASE_PORT: u16 = 4001;
-
+    /// Start a new session, moving it to the next free port range when a
+    /// port turns out to be taken by another program.
+    async fn start_with_port_recovery(
+        &self,
+        session: &mut Session,
+        eavs_virtual_key: Option<&str>,
+    ) -> Result<()> {
+        let mut attempt = 0;
+        loop {
+            let Err(err) = self.start_container(session, eavs_virtual_key).await else {
+                return Ok(());
+            };
+            if attempt == Self::MAX_PORT_CONFLICT_RETRIES {
+                return Err(err);
+            }
+            let Some(conflict) = port_conflict::detect(&Self::session_ports(session), &err) else {
+                return Err(err);
+            };
+            attempt += 1;
+            warn!("Starting session {} failed: {:#}", session.id, err);
+            // Clear what the failed start left behind; the retry reuses the
+            // container name.
+            match session.runtime_mode {
+                RuntimeMode::Container => {
+                    if let Some(runtime) = self.container_runtime() {
+                        let _ = runtime
+                            .remove_container(&session.container_name, true)
+                            .await;
+                    }
+                }
+                RuntimeMode::Local => {
+                    if let Ok(runner) = self.runner_for_user(&session.user_id) {
+                        let _ = runner.stop_session(&session.id).await;
+                    }
+                }
+            }
+            self.recover_from_port_conflict(session, conflict, attempt)
+                .await?;
+        }
+    }
+
+    /// Host ports a started session listens on.
+    fn session_ports(session: &Session) -> Vec<u16> {
+        let mut ports = vec![
+            session.agent_port as u16,
+            session.fileserver_port as u16,
+            session.ttyd_port as u16,
+        ];
+        // Containers publish their sub-agent and mmry ports too.
+        if session.runtime_mode == RuntimeMode::Container {
+            ports.extend(session.mmry_port.map(|port| port as u16));
+            if let (Some(agent_base), Some(max_agents)) =
+                (session.agent_base_port, session.max_agents)
+            {
+                ports.extend((agent_base..agent_base + max_agents).map(|port| port as u16));
+            }
+        }
+        ports
+    }
+
+    /// Move a session whose start hit a port conflict past its current port
+    /// range and announce the move.
+    async fn recover_from_port_conflict(
+        &self,
+        session: &mut Session,
+        conflict: PortConflict,
+        attempt: u32,
+    ) -> Result<()> {
+        let previous_ports = [
+            session.agent_port,
+            session.fileserver_port,
+            session.ttyd_port,
+        ];
+        let range_end = match (session.agent_base_port, session.max_agents) {
+            (Some(agent_base), Some(max_agents)) => agent_base + max_agents,
+            _ => session.ttyd_port + 1,
+        };
+        self.reassign_ports(session, range_end).await?;
+
+        let holder = conflict
+            .holder
+            .as_ref()
+            .map_or_else(|| "another program".to_string(), ToString::to_string);
+        match conflict.port {
+            Some(port) => warn!(
+                "Port {} of session {} is taken by {}; moved the session from ports {}/{}/{} (attempt {})",
+                port,
+                session.id,
+                holder,
+                previous_ports[0],
+                previous_ports[1],
+                previous_ports[2],
+                attempt
+            ),
+            None => warn!(
+                "Session {} hit a port conflict; moved the session from ports {}/{}/{} (attempt {})",
+                session.id, previous_ports[0], previous_ports[1], previous_ports[2], attempt
+            ),
+        }
+        // No receivers is fine.
+        let _ = self.port_conflicts.send(PortConflictEvent {
+            session_id: session.id.clone(),
+            user_id: session.user_id.clone(),
+            port: conflict.port,
+            holder: conflict.holder,
+            previous_ports,
+            ports: [
+                session.agent_port,
+                session.fileserver_port,
+                session.ttyd_port,
+            ],
+            attempt,
+        });
+        Ok(())
+    }
+
+    async fn start_agent_browser_daemon(&self, session: &Session) {
+        let browser_session_id = browser_session_name(&session.id);
+        if let Err(err) = self
+            .agent_browser
+            .ensure_session(&browser_session_id, Some(&session.user_id))
+            .await
+        {
+            warn!(
+                "Failed to start agent-browser daemon for session {} (browser session {}): {}",
+                session.id, browser_session_id, err
+            );
+        }
+    }
+
+    /// Pre-launch pooled agent-browser daemons (no-op unless pooling is
+    /// enabled). Call after stale daemons from earlier runs were cleaned up.
+    pub fn start_agent_browser_pool(&self) {
+        self.agent_browser.start_pool_task();
+    }
+
+    async fn stop_agent_browser_daemon(&self, session_id: &str) {
+        let browser_session_id = browser_session_name(session_id);
+        if let Err(err) = self.agent_browser.stop_session(&browser_session_id).await {
+            warn!(
+                "Failed to stop agent-browser daemon for session {} (browser session {}): {}",
+                session_id, browser_session_id, err
+            );
+        }
+    }
+
+    /// Internal port base for sub-agents inside the container.
+    const INTERNAL_AGENT_BASE_PORT: u16 = 4001;
+
     /// Start a container for the given session (container mode).
     async fn start_container_mode(
         &self,
@@ -1294,10 +1998,18 @@ impl SessionService {
         let runtime = self
             .container_runtime()
             .context("container runtime not available")?;
+        let rules = self.egress_rules(&session.id).await?;
+        let setup = self.startup_setup(&session.id).await;
+        let secrets = self
+            .session_secrets(&session.id, &session.user_id, &session.workspace_path)
+            .await;
 
         // Build container config
         // Mount the full user home directory so dotfiles and tool state persist across restarts.
+        // Setup variables and secrets come first so the ones set below win.
         let mut config = ContainerConfig::new(&session.image)
+            .envs(setup.env)
+            .envs(secrets.env.into_iter().collect())
             .name(&session.container_name)
             .hostname(&session.container_name)
             .port(session.agent_port as u16, 41820)
@@ -1306,7 +2018,37 @@ impl SessionService {
             .volume(&session.workspace_path, "/home/dev")
             .env("OPENCODE_PORT", "41820")
             .env("FILESERVER_PORT", "41821")
-            .env("TTYD_PORT", "41822");
+            .env("TTYD_PORT", "41822")
+            // Sessions created before limits were tracked get the defaults.
+            .resources(
+                session
+                    .resource_limits
+                    .clone()
+                    .unwrap_or_else(|| self.config.resource_limits.clone()),
+            );
+        for mount in setup.mounts {
+            config = if mount.read_only {
+                config.volume_ro(mount.source, mount.target)
+            } else {
+                config.volume(mount.source, mount.target)
+            };
+        }
+        // Always mounted, so secret files added later show up on resume.
+        if let Some(store) = &self.secrets {
+            let dir = store.write_container_files(&session.id, &secrets.files)?;
+            config = config
+                .volume_ro(dir.display().to_string(), CONTAINER_SECRETS_DIR)
+                .env(SECRETS_DIR_ENV, CONTAINER_SECRETS_DIR);
+        }
+
+        // A file rather than env, so resuming the container rotates it.
+        let token_dir = self.write_fileserver_token_file(&session.id).await?;
+        config = config
+            .volume_ro(token_dir.display().to_string(), CONTAINER_FILESERVER_DIR)
+            .env(
+                oqto_files::capability::TOKEN_SECRET_FILE_ENV,
+                format!("{CONTAINER_FILESERVER_DIR}/{FILESERVER_TOKEN_FILE}"),
+            );
 
         // Map sub-agent ports if configured
         // Each sub-agent gets a port: external (agent_base_port + i) -> internal (4001 + i)
@@ -1367,23 +2109,31 @@ impl SessionService {
             // Map pi-bridge port (internal 41824 -> same external port for simplicity)
             // The backend's ContainerPiRuntime will connect to localhost:41824 via the mapped port
             config = config.port(41824, 41824);
-            if let Some(ref provider) = self.config.pi_provider {
+            if let Some(provider) = setup.provider.as_ref().or(self.config.pi_provider.as_ref()) {
                 config = config.env("PI_PROVIDER", provider);
             }
-            if let Some(ref model) = self.config.pi_model {
+            if let Some(model) = setup.model.as_ref().or(self.config.pi_model.as_ref()) {
                 config = config.env("PI_MODEL", model);
             }
             info!("Enabled pi-bridge for session {} on port 41824", session.id);
         }
 
-        // Create and start the container
-        let container_id = runtime
-            .create_container(&config)
-            .await
+        // Create and start the container. With egress rules it is only
+        // created here and started once the rules are attached.
+        let created = if rules.is_some() {
+            runtime.create_stopped_container(&config).await
+        } else {
+            runtime.create_container(&config).await
+        };
+        let container_id = created
+            .inspect_err(|_| {
+                self.remove_container_secrets(&session.id);
+                self.remove_fileserver_token_file(&session.id);
+            })
             .context("creating container")?;
 
         info!(
-            "Started container {} for session {}",
+            "Created container {} for session {}",
             container_id, session.id
         );
 
@@ -1392,26 +2142,49 @@ impl SessionService {
             .set_container_id(&session.id, &container_id)
             .await?;
 
-        // Wait for core services to become reachable before marking the session running.
+        // Start the container behind its egress rules, then wait for core
+        // services to become reachable before marking the session running.
         // This avoids clients receiving 502s due to fixed-delay startup races.
-        if let Err(e) = self
-            .readiness
-            .wait_for_session_services(session.fileserver_port as u16, session.ttyd_port as u16)
-            .await
-        {
+        let started = match &rules {
+            Some(rules) => {
+                self.start_container_behind(
+                    runtime.as_ref(),
+                    &session.id,
+                    &session.user_id,
+                    &container_id,
+                    rules,
+                )
+                .await
+            }
+            None => Ok(()),
+        };
+        let ready = match started {
+            Ok(()) => {
+                self.readiness
+                    .wait_for_session_services(
+                        session.fileserver_port as u16,
+                        session.ttyd_port as u16,
+                    )
+                    .await
+            }
+            Err(e) => Err(e),
+        };
+        if let Err(e) = ready {
             // Best-effort cleanup: stop/remove the container, then surface the error.
             if let Err(stop_err) = runtime.stop_container(&container_id, Some(10)).await {
                 warn!(
-                    "Failed to stop container {} after readiness failure: {:?}",
+                    "Failed to stop container {} after failed start: {:?}",
                     container_id, stop_err
                 );
             }
             if let Err(rm_err) = runtime.remove_container(&container_id, true).await {
                 warn!(
-                    "Failed to remove container {} after readiness failure: {:?}",
+                    "Failed to remove container {} after failed start: {:?}",
                     container_id, rm_err
                 );
             }
+            self.remove_container_secrets(&session.id);
+            self.remove_fileserver_token_file(&session.id);
             return Err(e);
         }
 
@@ -1467,7 +2240,16 @@ impl SessionService {
         // Build environment variables for the processes.
         // This is the SINGLE authority for what env vars the agent sees.
         // spawn_as_user() calls env_clear() so only vars in this map are passed.
-        let mut env = crate::local::base_system_env();
+        // Setup variables go in first so the ones set below win.
+        let setup = self.startup_setup(&session.id).await;
+        let mut env = setup.env;
+        env.extend(crate::local::base_system_env());
+        if let Some(provider) = setup.provider {
+            env.insert("PI_PROVIDER".to_string(), provider);
+        }
+        if let Some(model) = setup.model {
+            env.insert("PI_MODEL".to_string(), model);
+        }
         if let Some(ref eavs_url) = self.config.eavs_container_url {
             env.insert("EAVS_URL".to_string(), eavs_url.clone());
         }
@@ -1514,12 +2296,18 @@ impl SessionService {
         }
 
         let workspace_path = PathBuf::from(&session.workspace_path);
+        let shell = WorkspaceConfig::load(&workspace_path).shell_environment(&workspace_path);
 
         info!(
             "Starting session {} via runner for user {}",
             session.id, session.user_id
         );
 
+        let token_secret = self.rotate_fileserver_token_secret(&session.id).await?;
+        let secrets = self
+            .session_secrets(&session.id, &session.user_id, &session.workspace_path)
+            .await;
+
         // Start services via runner
         let response = runner
             .start_session(
@@ -1530,6 +2318,9 @@ impl SessionService {
                 ttyd_port,
                 session.agent.clone(),
                 env,
+                Some(token_secret),
+                shell,
+                secrets,
             )
             .await
             .context("starting session via runner")?;
@@ -1607,6 +2398,10 @@ impl SessionService {
                 {
                     warn!("Failed to stop container {}: {:?}", container_id, e);
                 }
+                if let Some(egress) = &self.egress {
+                    egress.release(session_id);
+                }
+                self.remove_container_secrets(session_id);
                 // Container is NOT removed - it can be restarted with resume_session()
             }
             RuntimeMode::Local => {
@@ -1644,6 +2439,138 @@ impl SessionService {
         Ok(())
     }
 
+    /// Hibernate a session: save its state, then stop it. The next
+    /// `resume_session()` restores the state.
+    ///
+    /// For container mode: checkpoints the container's processes (CRIU), so
+    /// agent conversations and running shells come back as they were.
+    /// For local mode: records the agent sessions running in the workspace
+    /// and closes them; resuming reopens them from their session files.
+    /// Falls back to `stop_session()` when the state cannot be saved.
+    pub async fn hibernate_session(&self, session_id: &str) -> Result<()> {
+        let session = self
+            .repo
+            .get(session_id)
+            .await?
+            .ok_or_else(|| anyhow::anyhow!("session not found: {}", session_id))?;
+
+        if session.status != SessionStatus::Running {
+            return self.stop_session(session_id).await;
+        }
+
+        info!(
+            "Hibernating session {} ({:?} mode)",
+            session_id, session.runtime_mode
+        );
+        let hibernation = match session.runtime_mode {
+            RuntimeMode::Container => self.checkpoint_session_container(&session).await,
+            RuntimeMode::Local => self.close_session_agents(&session).await,
+        };
+        let hibernation = match hibernation {
+            Ok(Some(hibernation)) => hibernation,
+            Ok(None) => {
+                info!(
+                    "Session {} has agents mid-turn, hibernating it later",
+                    session_id
+                );
+                return Ok(());
+            }
+            Err(e) => {
+                warn!(
+                    "Failed to hibernate session {}, stopping it instead: {:?}",
+                    session_id, e
+                );
+                return self.stop_session(session_id).await;
+            }
+        };
+
+        // Releases what the checkpoint left running (services, mmry, browser).
+        self.stop_session(session_id).await?;
+        self.repo.mark_hibernated(session_id, &hibernation).await?;
+        info!("Session {} hibernated", session_id);
+
+        Ok(())
+    }
+
+    /// Checkpoint the session's container, which stops it.
+    async fn checkpoint_session_container(&self, session: &Session) -> Result<Option<Hibernation>> {
+        let container_id = session
+            .container_id
+            .as_deref()
+            .ok_or_else(|| anyhow::anyhow!("session has no container to checkpoint"))?;
+        let runtime = self
+            .container_runtime()
+            .context("container runtime not available")?;
+        // A restore could not be held for the rules, see `resume_container`.
+        if self.egress_rules(&session.id).await?.is_some() {
+            anyhow::bail!("containers with egress rules are not checkpointed");
+        }
+
+        let checkpoint = format!("hibernate-{}", Utc::now().timestamp());
+        runtime
+            .checkpoint_container(container_id, &checkpoint)
+            .await
+            .context("checkpointing container")?;
+
+        Ok(Some(Hibernation {
+            hibernated_at: Utc::now().to_rfc3339(),
+            checkpoint: Some(checkpoint),
+            agents: Vec::new(),
+        }))
+    }
+
+    /// Close the agent sessions running in a local session's workspace,
+    /// recording them for the resume. None while one of them is mid-turn.
+    async fn close_session_agents(&self, session: &Session) -> Result<Option<Hibernation>> {
+        let runner = self.runner_for_user(&session.user_id)?;
+        let running = runner
+            .agent_list_sessions()
+            .await
+            .context("listing agent sessions")?;
+        let Some(agents) = workspace_agents(running, &session.workspace_path) else {
+            return Ok(None);
+        };
+
+        for agent in &agents {
+            if let Err(e) = runner.agent_close_session(&agent.session_id).await {
+                warn!(
+                    "Failed to close agent session {} of {}: {:?}",
+                    agent.session_id, session.id, e
+                );
+            }
+        }
+
+        Ok(Some(Hibernation {
+            hibernated_at: Utc::now().to_rfc3339(),
+            checkpoint: None,
+            agents,
+        }))
+    }
+
+    /// Reopen the agent sessions of a hibernated local session. Each resumes
+    /// from its session file.
+    async fn reopen_session_agents(&self, runner: &RunnerClient, agents: &[HibernatedAgent]) {
+        for agent in agents {
+            let request = PiCreateSessionRequest {
+                session_id: agent.session_id.clone(),
+                config: PiSessionConfig {
+                    cwd: PathBuf::from(&agent.cwd),
+                    provider: agent.provider.clone(),
+                    model: agent.model.clone(),
+                    harness: agent.harness.clone(),
+                    sandbox_profile: agent.sandbox_profile.clone(),
+                    ..Default::default()
+                },
+            };
+            if let Err(e) = runner.agent_create_session(request).await {
+                warn!(
+                    "Failed to reopen agent session {}: {:?}",
+                    agent.session_id, e
+                );
+            }
+        }
+    }
+
     /// Resume a stopped session by restarting its services.
     ///
     /// For container mode: restarts the stopped container.
@@ -1670,9 +2597,16 @@ impl SessionService {
                 session.status
             );
         }
-
-        // Check if image has been updated - if so, upgrade instead of resume (container mode only)
+        self.enforce_quarantine_cap(&session.user_id).await?;
+
+        // Check if image has been updated - if so, upgrade instead of resume (container mode only).
+        // An upgrade would discard a hibernation checkpoint; it waits for the next resume.
+        let checkpointed = session
+            .hibernation
+            .as_ref()
+            .is_some_and(|h| h.checkpoint.is_some());
         if session.runtime_mode == RuntimeMode::Container
+            && !checkpointed
             && let Ok(Some(new_digest)) = self.check_for_image_update(session_id).await
         {
             info!(
@@ -1696,7 +2630,8 @@ impl SessionService {
             if session.runtime_mode == RuntimeMode::Local
                 && Self::is_retryable_unique_violation(&err)
             {
-                self.reassign_ports_for_resume(&mut session).await?;
+                self.reassign_ports(&mut session, self.config.base_port)
+                    .await?;
                 self.repo
                     .update_status(session_id, SessionStatus::Starting)
                     .await?;
@@ -1719,7 +2654,8 @@ impl SessionService {
         result
     }
 
-    async fn reassign_ports_for_resume(&self, session: &mut Session) -> Result<()> {
+    /// Move a session to the first usable port range from `search_start`.
+    async fn reassign_ports(&self, session: &mut Session, search_start: i64) -> Result<()> {
         let max_agents = session.max_agents.unwrap_or(Self::DEFAULT_MAX_AGENTS);
 
         // Container mode uses a per-session mmry port (base+3) when enabled.
@@ -1730,11 +2666,7 @@ impl SessionService {
             && session.mmry_port.is_some();
 
         let base_port = self
-            .find_usable_port_range_with_agents(
-                self.config.base_port,
-                max_agents,
-                include_mmry_port,
-            )
+            .find_usable_port_range_with_agents(search_start, max_agents, include_mmry_port)
             .await?;
 
         let new_mmry_port = if include_mmry_port {
@@ -1788,8 +2720,16 @@ impl SessionService {
                     .container_runtime()
                     .context("container runtime not available")?;
 
-                // Start the existing container
-                if let Err(e) = runtime.start_container(container_id).await {
+                // Start the existing container, from its checkpoint when
+                // hibernated, with fresh secret files
+                let started = match self
+                    .write_container_secrets(session_id, &session.user_id, &session.workspace_path)
+                    .await
+                {
+                    Ok(()) => self.resume_container(runtime.as_ref(), session).await,
+                    Err(e) => Err(e),
+                };
+                if let Err(e) = started {
                     error!(
                         "Failed to start container {} for session {}: {:?}",
                         container_id, session_id, e
@@ -1936,63 +2876,90 @@ impl SessionService {
                 }
 
                 let workspace_path = PathBuf::from(&session.workspace_path);
+                let shell =
+                    WorkspaceConfig::load(&workspace_path).shell_environment(&workspace_path);
+                let secrets = self
+                    .session_secrets(session_id, &session.user_id, &session.workspace_path)
+                    .await;
 
                 // Stop any stale session state in the runner (ignore errors - session may not exist)
                 let _ = runner.stop_session(session_id).await;
 
-                // Respawn the processes via runner
-                match runner
-                    .start_session(
-                        session_id,
-                        &workspace_path,
-                        agent_port,
-                        fileserver_port,
-                        ttyd_port,
-                        session.agent.clone(),
-                        env,
-                    )
-                    .await
-                {
-                    Ok(response) => {
-                        // Update with new PIDs
-                        self.repo
-                            .set_container_id(session_id, &response.pids)
-                            .await?;
-                    }
-                    Err(e) => {
-                        error!(
-                            "Failed to resume local services for session {}: {:?}",
-                            session_id, e
-                        );
-                        self.repo
-                            .mark_failed(session_id, &format!("resume failed: {}", e))
+                // Moved to another port range and retried when a port turns
+                // out to be taken by another program.
+                let mut attempt = 0;
+                loop {
+                    // Tokens minted before the resume stop working.
+                    let token_secret = self.rotate_fileserver_token_secret(session_id).await?;
+
+                    // Respawn the processes via runner
+                    let failure = match runner
+                        .start_session(
+                            session_id,
+                            &workspace_path,
+                            agent_port,
+                            fileserver_port,
+                            ttyd_port,
+                            session.agent.clone(),
+                            env.clone(),
+                            Some(token_secret),
+                            shell.clone(),
+                            secrets.clone(),
+                        )
+                        .await
+                    {
+                        Ok(response) => {
+                            // Update with new PIDs
+                            self.repo
+                                .set_container_id(session_id, &response.pids)
+                                .await?;
+                            // Wait for services to become ready
+                            match self
+                                .readiness
+                                .wait_for_session_services(fileserver_port, ttyd_port)
+                                .await
+                            {
+                                Ok(()) => None,
+                                Err(e) => {
+                                    let _ = runner.stop_session(session_id).await;
+                                    Some(("services not ready after resume", e))
+                                }
+                            }
+                        }
+                        Err(e) => Some(("resume failed", e)),
+                    };
+                    let Some((reason, e)) = failure else {
+                        break;
+                    };
+
+                    if attempt < Self::MAX_PORT_CONFLICT_RETRIES
+                        && let Some(conflict) =
+                            port_conflict::detect(&Self::session_ports(session), &e)
+                    {
+                        attempt += 1;
+                        let _ = runner.stop_session(session_id).await;
+                        self.recover_from_port_conflict(session, conflict, attempt)
                             .await?;
-                        return Ok(self.repo.get(session_id).await?.unwrap_or(session.clone()));
+                        agent_port = session.agent_port as u16;
+                        fileserver_port = session.fileserver_port as u16;
+                        ttyd_port = session.ttyd_port as u16;
+                        continue;
                     }
-                }
 
-                // Wait for services to become ready
-                if let Err(e) = self
-                    .readiness
-                    .wait_for_session_services(
-                        session.fileserver_port as u16,
-                        session.ttyd_port as u16,
-                    )
-                    .await
-                {
                     error!(
-                        "Services not ready after resume for session {}: {:?}",
-                        session_id, e
+                        "Failed to resume local services for session {} ({}): {:?}",
+                        session_id, reason, e
                     );
-                    let _ = runner.stop_session(session_id).await;
                     self.repo
-                        .mark_failed(
-                            session_id,
-                            &format!("services not ready after resume: {}", e),
-                        )
+                        .mark_failed(session_id, &format!("{}: {}", reason, e))
                         .await?;
                     return Ok(self.repo.get(session_id).await?.unwrap_or(session.clone()));
                 }
+
+                if let Some(ref hibernation) = session.hibernation {
+                    self.reopen_session_agents(&runner, &hibernation.agents)
+                        .await;
+                }
             }
         }
 
@@ -2013,6 +2980,88 @@ impl SessionService {
         }
     }
 
+    /// Mint a short-lived capability token for a request to the session's
+    /// fileserver, confined to `root`. None for sessions whose fileserver runs
+    /// without a secret (started before tokens were introduced).
+    pub async fn fileserver_token(
+        &self,
+        session_id: &str,
+        root: &str,
+        scope: oqto_files::capability::CapabilityScope,
+    ) -> Result<Option<String>> {
+        let Some(secret) = self.repo.fileserver_token_secret(session_id).await? else {
+            return Ok(None);
+        };
+        let token = oqto_files::CapabilityKey::new(&secret)
+            .mint(session_id, root, scope, FILESERVER_TOKEN_TTL)
+            .context("minting fileserver token")?;
+        Ok(Some(token))
+    }
+
+    /// Generate and store a new fileserver token secret, invalidating every
+    /// token minted for the session before.
+    async fn rotate_fileserver_token_secret(&self, session_id: &str) -> Result<String> {
+        let bytes: [u8; 32] = rand::random();
+        let secret = hex::encode(bytes);
+        self.repo
+            .set_fileserver_token_secret(session_id, &secret)
+            .await?;
+        Ok(secret)
+    }
+
+    /// Host directory mounted at [`CONTAINER_FILESERVER_DIR`] in a session's
+    /// container. Lives next to the users' homes, never inside one.
+    fn fileserver_token_dir(&self, session_id: &str) -> std::path::PathBuf {
+        std::path::Path::new(&self.config.user_data_path)
+            .join("fileserver-tokens")
+            .join(session_id)
+    }
+
+    /// Rotate a container session's fileserver token secret and (re)write
+    /// the file its fileserver reads it from. Returns the directory to mount.
+    /// Only the server user can enter its parent; the file is readable by
+    /// all so the container's user can read it whatever uid it maps to.
+    async fn write_fileserver_token_file(&self, session_id: &str) -> Result<std::path::PathBuf> {
+        let dir = self.fileserver_token_dir(session_id);
+        std::fs::create_dir_all(&dir).with_context(|| format!("creating {}", dir.display()))?;
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            if let Some(parent) = dir.parent() {
+                std::fs::set_permissions(parent, std::fs::Permissions::from_mode(0o700))?;
+            }
+            std::fs::set_permissions(&dir, std::fs::Permissions::from_mode(0o755))?;
+        }
+        let secret = self.rotate_fileserver_token_secret(session_id).await?;
+        // Renamed into place, so the fileserver never reads half a secret.
+        let staged = dir.join(format!(".{FILESERVER_TOKEN_FILE}.new"));
+        std::fs::write(&staged, &secret)
+            .with_context(|| format!("writing {}", staged.display()))?;
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            std::fs::set_permissions(&staged, std::fs::Permissions::from_mode(0o644))?;
+        }
+        std::fs::rename(&staged, dir.join(FILESERVER_TOKEN_FILE))
+            .context("replacing the fileserver token secret")?;
+        Ok(dir)
+    }
+
+    /// Give a container's fileserver a fresh token secret before it starts
+    /// again. Containers created before the secret was mounted keep the one
+    /// in their environment.
+    async fn rewrite_fileserver_token_file(&self, session_id: &str) -> Result<()> {
+        if self.fileserver_token_dir(session_id).exists() {
+            self.write_fileserver_token_file(session_id).await?;
+        }
+        Ok(())
+    }
+
+    /// Remove the fileserver token secret of a removed container.
+    fn remove_fileserver_token_file(&self, session_id: &str) {
+        let _ = std::fs::remove_dir_all(self.fileserver_token_dir(session_id));
+    }
+
     /// List all sessions.
     pub async fn list_sessions(&self) -> Result<Vec<Session>> {
         let sessions = self.repo.list().await?;
@@ -2025,6 +3074,50 @@ impl SessionService {
         Ok(reconciled)
     }
 
+    /// Run a command inside a running container session and return its stdout.
+    pub async fn exec_in_session(&self, session: &Session, command: &[&str]) -> Result<String> {
+        let runtime = self
+            .container_runtime()
+            .context("no container runtime configured")?;
+        let container_id = session
+            .container_id
+            .as_deref()
+            .filter(|_| session.runtime_mode == RuntimeMode::Container)
+            .context("session has no container")?;
+        Ok(runtime.exec_output(container_id, command).await?)
+    }
+
+    /// Whether images can be built here (container mode).
+    pub fn can_build_images(&self) -> bool {
+        self.container_runtime().is_some()
+    }
+
+    /// Build a container image with the container runtime, sending the
+    /// build output to `output` line by line.
+    pub async fn build_image(
+        &self,
+        spec: &ImageBuildSpec,
+        output: tokio::sync::mpsc::Sender<String>,
+    ) -> Result<()> {
+        let runtime = self
+            .container_runtime()
+            .context("no container runtime configured")?;
+        Ok(runtime.build_image(spec, output).await?)
+    }
+
+    /// Address of a container session on its container network.
+    pub async fn session_container_ip(&self, session: &Session) -> Result<Option<IpAddr>> {
+        let runtime = self
+            .container_runtime()
+            .context("no container runtime configured")?;
+        let container_id = session
+            .container_id
+            .as_deref()
+            .filter(|_| session.runtime_mode == RuntimeMode::Container)
+            .context("session has no container")?;
+        Ok(runtime.container_ip(container_id).await?)
+    }
+
     /// Collect container stats for all container-mode sessions.
     /// Returns an empty report if no container runtime is configured (local mode).
     pub async fn collect_container_stats(&self) -> Result<ContainerStatsReport> {
@@ -2065,8 +3158,74 @@ impl SessionService {
         Ok(ContainerStatsReport { stats, errors })
     }
 
+    /// Read the current resource usage of a running session: container
+    /// stats in container mode, the runner's readings of the session's
+    /// processes in local mode.
+    pub async fn sample_session_resources(&self, session: &Session) -> Result<ResourceReading> {
+        let at = Utc::now().timestamp_millis();
+        match session.runtime_mode {
+            RuntimeMode::Container => {
+                let runtime = self
+                    .container_runtime()
+                    .context("no container runtime configured")?;
+                let container_id = session
+                    .container_id
+                    .as_deref()
+                    .context("session has no container")?;
+                let stats = runtime.get_stats(container_id).await?;
+                let (memory_bytes, memory_limit_bytes) =
+                    resources::parse_memory_usage(&stats.mem_usage);
+                let has_gpus = !session
+                    .resource_limits
+                    .as_ref()
+                    .unwrap_or(&self.config.resource_limits)
+                    .gpu_devices()
+                    .is_empty();
+                let gpu = if has_gpus {
+                    match runtime
+                        .exec_output(container_id, resources::NVIDIA_SMI_QUERY)
+                        .await
+                    {
+                        Ok(output) => resources::parse_gpu_usage(&output),
+                        Err(e) => {
+                            debug!("GPU query in session {} failed: {:?}", session.id, e);
+                            None
+                        }
+                    }
+                } else {
+                    None
+                };
+                Ok(ResourceReading {
+                    at,
+                    cpu: CpuReading::Percent(
+                        resources::parse_percent(&stats.cpu_percent).unwrap_or(0.0),
+                    ),
+                    memory_bytes: memory_bytes.unwrap_or(0),
+                    memory_limit_bytes,
+                    processes: stats.pids.trim().parse().ok(),
+                    gpu_percent: gpu.map(|(percent, _)| percent),
+                    gpu_memory_bytes: gpu.map(|(_, bytes)| bytes),
+                })
+            }
+            RuntimeMode::Local => {
+                let usage = self
+                    .runner_for_user(&session.user_id)?
+                    .get_session_resources(&session.id)
+                    .await?;
+                Ok(ResourceReading {
+                    at: usage.sampled_at,
+                    cpu: CpuReading::TimeUs(usage.cpu_time_us),
+                    memory_bytes: usage.memory_bytes,
+                    memory_limit_bytes: None,
+                    processes: Some(usage.processes),
+                    gpu_percent: None,
+                    gpu_memory_bytes: usage.gpu_memory_bytes,
+                })
+            }
+        }
+    }
+
     /// List active sessions.
-    #[allow(dead_code)]
     pub async fn list_active_sessions(&self) -> Result<Vec<Session>> {
         self.repo.list_active().await
     }
@@ -2105,6 +3264,11 @@ impl SessionService {
                 {
                     // Try to stop first (in case it's somehow still running)
                     let _ = runtime.stop_container(container_id, Some(5)).await;
+                    if let Some(egress) = &self.egress {
+                        egress.release(session_id);
+                    }
+                    self.remove_container_secrets(session_id);
+                    self.remove_fileserver_token_file(session_id);
 
                     // Remove the container
                     if let Err(e) = runtime.remove_container(container_id, true).await {
@@ -2345,6 +3509,83 @@ impl SessionService {
         Ok(updated_session)
     }
 
+    /// Limits new container sessions of the user get: the user's admin
+    /// overrides with `[container.resources]` filling the unset ones.
+    pub async fn effective_resource_limits(&self, user_id: &str) -> Result<ResourceLimits> {
+        Ok(self
+            .repo
+            .get_user_resource_limits(user_id)
+            .await?
+            .unwrap_or_default()
+            .or(&self.config.resource_limits))
+    }
+
+    /// The user's resource limit overrides next to the limits in effect.
+    pub async fn user_resource_limits(&self, user_id: &str) -> Result<UserResourceLimits> {
+        Ok(UserResourceLimits {
+            user_id: user_id.to_string(),
+            overrides: self.repo.get_user_resource_limits(user_id).await?,
+            effective: self.effective_resource_limits(user_id).await?,
+        })
+    }
+
+    /// Set (or with `None` clear) the user's resource limit overrides. Only
+    /// sessions created afterwards pick them up.
+    pub async fn set_user_resource_limits(
+        &self,
+        user_id: &str,
+        limits: Option<&ResourceLimits>,
+    ) -> Result<UserResourceLimits> {
+        match limits {
+            Some(limits) => {
+                limits.validate()?;
+                self.repo.set_user_resource_limits(user_id, limits).await?;
+            }
+            None => {
+                self.repo.delete_user_resource_limits(user_id).await?;
+            }
+        }
+        self.user_resource_limits(user_id).await
+    }
+
+    /// Override a container session's resource limits.
+    ///
+    /// CPU, memory and pids limits are applied to an existing container right
+    /// away; a GPU change takes effect once the container is recreated (see
+    /// [`Self::upgrade_session`]).
+    pub async fn set_session_resource_limits(
+        &self,
+        session_id: &str,
+        limits: &ResourceLimits,
+    ) -> Result<Session> {
+        limits.validate()?;
+        let session = self
+            .repo
+            .get(session_id)
+            .await?
+            .ok_or_else(|| anyhow::anyhow!("session not found: {}", session_id))?;
+        if session.runtime_mode == RuntimeMode::Local {
+            anyhow::bail!("resource limits cannot be set on local mode sessions");
+        }
+
+        self.repo.set_resource_limits(session_id, limits).await?;
+        if let Some(ref container_id) = session.container_id {
+            let runtime = self
+                .container_runtime()
+                .context("container runtime not available")?;
+            runtime
+                .update_resources(container_id, limits)
+                .await
+                .with_context(|| format!("updating resources of container {container_id}"))?;
+            info!("Updated resource limits of session {}", session_id);
+        }
+
+        self.repo
+            .get(session_id)
+            .await?
+            .ok_or_else(|| anyhow::anyhow!("session not found: {}", session_id))
+    }
+
     async fn reconcile_session_container_state(&self, session: Session) -> Result<Session> {
         if !session.is_active() {
             return Ok(session);
@@ -2387,6 +3628,21 @@ impl SessionService {
             }
             // Container is stopped/exited - attempt to restart it
             Ok(Some(status)) if status == "exited" || status == "stopped" || status == "dead" => {
+                let exit_info = Self::container_exit_info(runtime, container_id).await;
+                if let Some(info) = &exit_info {
+                    warn!(
+                        "Container {} for session {} exited: {}",
+                        container_id, session.id, info.hint
+                    );
+                    // Restarting would hit the same failure again.
+                    if info.category.is_permanent() {
+                        self.repo
+                            .mark_failed_with_exit(&session.id, &info.hint, info)
+                            .await?;
+                        return Ok(self.repo.get(&session.id).await?.unwrap_or(session));
+                    }
+                }
+
                 info!(
                     "Container {} for session {} is {} - attempting restart",
                     container_id, session.id, status
@@ -2400,7 +3656,9 @@ impl SessionService {
                 // Spawn the restart in the background to avoid blocking the request
                 let service = self.clone();
                 let session_id = session.id.clone();
+                let user_id = session.user_id.clone();
                 let container_id_owned = container_id.to_string();
+                let workspace_path = session.workspace_path.clone();
                 let _agent_port = session.agent_port as u16;
                 let fileserver_port = session.fileserver_port as u16;
                 let ttyd_port = session.ttyd_port as u16;
@@ -2417,15 +3675,36 @@ impl SessionService {
                         }
                     };
 
-                    // Start the container
-                    if let Err(e) = runtime.start_container(&container_id_owned).await {
+                    // Start the container, with fresh secret files
+                    let started = match service
+                        .write_container_secrets(&session_id, &user_id, &workspace_path)
+                        .await
+                    {
+                        Ok(()) => {
+                            service
+                                .start_stopped_container(
+                                    runtime.as_ref(),
+                                    &session_id,
+                                    &user_id,
+                                    &container_id_owned,
+                                )
+                                .await
+                        }
+                        Err(e) => Err(e),
+                    };
+                    if let Err(e) = started {
                         error!(
                             "Failed to restart container {} for session {}: {:?}",
                             container_id_owned, session_id, e
                         );
+                        let message = format!("restart failed: {}", e);
                         let _ = service
                             .repo
-                            .mark_failed(&session_id, &format!("restart failed: {}", e))
+                            .mark_failed_with_exit(
+                                &session_id,
+                                &message,
+                                &exit_info.unwrap_or_else(|| ExitInfo::from_error(&message)),
+                            )
                             .await;
                         return;
                     }
@@ -2445,11 +3724,13 @@ impl SessionService {
                             "Services not ready after restart for session {}: {:?}",
                             session_id, e
                         );
+                        let message = format!("services not ready after restart: {}", e);
                         let _ = service
                             .repo
-                            .mark_failed(
+                            .mark_failed_with_exit(
                                 &session_id,
-                                &format!("services not ready after restart: {}", e),
+                                &message,
+                                &exit_info.unwrap_or_else(|| ExitInfo::from_error(&message)),
                             )
                             .await;
                         return;
@@ -2487,6 +3768,36 @@ impl SessionService {
         }
     }
 
+    /// Exit diagnostics of a stopped container, from its state and the tail
+    /// of its output.
+    async fn container_exit_info(
+        runtime: &Arc<dyn ContainerRuntimeApi>,
+        container_id: &str,
+    ) -> Option<ExitInfo> {
+        let state = match runtime.container_exit_state(container_id).await {
+            Ok(state) => state?,
+            Err(e) => {
+                debug!(
+                    "Failed to inspect exit of container {}: {:?}",
+                    container_id, e
+                );
+                return None;
+            }
+        };
+        let logs = runtime
+            .tail_logs(container_id, EXIT_LOG_TAIL_LINES)
+            .await
+            .unwrap_or_default();
+        let output = format!("{}\n{}", state.error, logs);
+        Some(ExitInfo::classify(ExitEvidence {
+            exit_code: Some(state.exit_code),
+            oom_killed: state.oom_killed,
+            memory_limit: state.memory_limit,
+            output: &output,
+            ..Default::default()
+        }))
+    }
+
     fn parse_local_session_pids(container_id: Option<&str>) -> Option<Vec<u32>> {
         let container_id = container_id?;
         let pids: Vec<u32> = container_id
@@ -2553,9 +3864,9 @@ impl SessionService {
             Ok(session)
         } else {
             // Get detailed exit info to help debug why processes stopped
-            let exit_info = local_runtime.get_session_exit_info(&session.id).await;
+            let exit_statuses = local_runtime.get_session_exit_statuses(&session.id).await;
 
-            if exit_info.is_empty() {
+            if exit_statuses.is_empty() {
                 warn!(
                     "Local processes for session {} are not running (no exit info available), marking as stopped",
                     session.id
@@ -2563,9 +3874,15 @@ impl SessionService {
                 self.repo.mark_stopped(&session.id).await?;
             } else {
                 // Format exit reasons for the error message
-                let reasons: Vec<String> = exit_info
+                let reasons: Vec<String> = exit_statuses
                     .iter()
-                    .map(|(service, reason)| format!("{}: {}", service, reason))
+                    .map(|(service, code, signal)| {
+                        format!(
+                            "{}: {}",
+                            service,
+                            ProcessHandle::format_exit_status(*code, *signal)
+                        )
+                    })
                     .collect();
                 let error_message =
                     format!("Processes stopped unexpectedly: {}", reasons.join("; "));
@@ -2575,8 +3892,19 @@ impl SessionService {
                     session.id, error_message
                 );
 
+                // The first process to exit usually took the others down.
+                let (service, exit_code, signal) = &exit_statuses[0];
+                let exit_info = ExitInfo::classify(ExitEvidence {
+                    exit_code: *exit_code,
+                    signal: *signal,
+                    service: Some(service),
+                    ..Default::default()
+                });
+
                 // Use mark_failed instead of mark_stopped to preserve the error message
-                self.repo.mark_failed(&session.id, &error_message).await?;
+                self.repo
+                    .mark_failed_with_exit(&session.id, &error_message, &exit_info)
+                    .await?;
             }
 
             Ok(self.repo.get(&session.id).await?.unwrap_or(session))
@@ -2908,6 +4236,7 @@ impl SessionService {
             image: None,
             agent: None,
             env: Default::default(),
+            sandbox_profile: None,
         };
 
         self.create_session_for_user(user_id, request).await
@@ -2948,33 +4277,38 @@ impl SessionService {
             image: None,
             agent: None,
             env: Default::default(),
+            sandbox_profile: None,
         };
 
-        self.create_session_with_readiness(user_id, request).await
+        self.create_session_with_readiness(user_id, request, None)
+            .await
     }
 
     /// Enforce the maximum concurrent sessions cap using LRU policy.
     ///
     /// If the user has reached the limit, stop the oldest idle session.
     async fn enforce_session_cap(&self, user_id: &str) -> Result<()> {
-        if self.config.max_concurrent_sessions <= 0 {
+        self.enforce_quarantine_cap(user_id).await?;
+
+        let limits = self.limits();
+        if limits.max_concurrent_sessions <= 0 {
             return Ok(());
         }
 
         let running_count = self.repo.count_running_for_user(user_id).await?;
 
-        if running_count < self.config.max_concurrent_sessions {
+        if running_count < limits.max_concurrent_sessions {
             return Ok(());
         }
 
         info!(
             "User {} has {} running sessions (limit: {}), stopping oldest",
-            user_id, running_count, self.config.max_concurrent_sessions
+            user_id, running_count, limits.max_concurrent_sessions
         );
 
         let idle_sessions = self
             .repo
-            .list_idle_sessions(self.config.idle_timeout_minutes)
+            .list_idle_sessions(limits.idle_timeout_minutes)
             .await?;
         if let Some(oldest_idle) = idle_sessions.first() {
             info!(
@@ -2985,27 +4319,56 @@ impl SessionService {
         } else {
             anyhow::bail!(
                 "active sessions at limit ({}); no idle sessions available to stop",
-                self.config.max_concurrent_sessions
+                limits.max_concurrent_sessions
             );
         }
 
         Ok(())
     }
 
-    /// Stop sessions that have been idle for too long.
+    /// Refuse to start another session for a quarantined user at the
+    /// quarantine quota. Their other sessions are never stopped to make room.
+    async fn enforce_quarantine_cap(&self, user_id: &str) -> Result<()> {
+        let Some(registration) = &self.registration else {
+            return Ok(());
+        };
+        let Some(cap) = registration.quarantine_session_cap(user_id).await? else {
+            return Ok(());
+        };
+        if self.repo.count_running_for_user(user_id).await? >= cap {
+            anyhow::bail!(
+                "unverified accounts cannot run more than {cap} session(s); \
+                 verify your email address to lift the limit"
+            );
+        }
+        Ok(())
+    }
+
+    /// Stop (or hibernate, depending on `action`) sessions that have been
+    /// idle for too long.
     ///
     /// This should be called periodically (e.g., by a background task).
     /// Returns the number of sessions stopped.
-    pub async fn stop_idle_sessions(&self, idle_minutes: i64) -> Result<usize> {
+    pub async fn stop_idle_sessions(&self, idle_minutes: i64, action: IdleAction) -> Result<usize> {
         let idle_sessions = self.repo.list_idle_sessions(idle_minutes).await?;
         let mut stopped = 0;
 
         for session in idle_sessions {
             info!(
-                "Stopping idle session {} (last activity: {:?}, idle > {} min)",
-                session.id, session.last_activity_at, idle_minutes
+                "{} idle session {} (last activity: {:?}, idle > {} min)",
+                match action {
+                    IdleAction::Stop => "Stopping",
+                    IdleAction::Hibernate => "Hibernating",
+                },
+                session.id,
+                session.last_activity_at,
+                idle_minutes
             );
-            if let Err(e) = self.stop_session(&session.id).await {
+            let result = match action {
+                IdleAction::Stop => self.stop_session(&session.id).await,
+                IdleAction::Hibernate => self.hibernate_session(&session.id).await,
+            };
+            if let Err(e) = result {
                 warn!("Failed to stop idle session {}: {:?}", session.id, e);
             } else {
                 stopped += 1;
@@ -3020,16 +4383,17 @@ impl SessionService {
     }
 
     /// Start a background task to periodically clean up idle sessions.
+    /// The idle timeout is read from the current limits on every check.
     ///
     /// Returns a handle that can be used to stop the task.
     pub fn start_idle_session_cleanup_task(
         self: Arc<Self>,
         check_interval_seconds: u64,
-        idle_timeout_minutes: i64,
     ) -> tokio::task::JoinHandle<()> {
         info!(
             "Starting idle session cleanup task (check every {}s, timeout {}min)",
-            check_interval_seconds, idle_timeout_minutes
+            check_interval_seconds,
+            self.limits().idle_timeout_minutes
         );
 
         tokio::spawn(async move {
@@ -3039,7 +4403,11 @@ impl SessionService {
             loop {
                 interval.tick().await;
 
-                if let Err(e) = self.stop_idle_sessions(idle_timeout_minutes).await {
+                let limits = self.limits();
+                if let Err(e) = self
+                    .stop_idle_sessions(limits.idle_timeout_minutes, limits.idle_action)
+                    .await
+                {
                     warn!("Idle session cleanup failed: {:?}", e);
                 }
             }
@@ -3047,6 +4415,71 @@ impl SessionService {
     }
 }
 
+/// Start a stopped session container, restoring its hibernation checkpoint
+/// when it has one. A checkpoint that fails to restore is skipped.
+async fn start_or_restore_container(
+    runtime: &dyn ContainerRuntimeApi,
+    session: &Session,
+) -> crate::container::ContainerResult<()> {
+    let container_id = session.container_id.as_deref().unwrap_or_default();
+    let checkpoint = session
+        .hibernation
+        .as_ref()
+        .and_then(|h| h.checkpoint.as_deref());
+    if let Some(checkpoint) = checkpoint {
+        match runtime.restore_container(container_id, checkpoint).await {
+            Ok(()) => {
+                info!(
+                    "Restored session {} from checkpoint {}",
+                    session.id, checkpoint
+                );
+                return Ok(());
+            }
+            Err(e) => warn!(
+                "Failed to restore checkpoint {} of session {}, starting afresh: {:?}",
+                checkpoint, session.id, e
+            ),
+        }
+    }
+    runtime.start_container(container_id).await
+}
+
+/// Agent sessions running in `workspace_path`, as recorded for a
+/// hibernation. None while one of them is mid-turn: closing it would cut the
+/// turn short.
+fn workspace_agents(
+    sessions: Vec<PiSessionInfo>,
+    workspace_path: &str,
+) -> Option<Vec<HibernatedAgent>> {
+    let workspace = std::path::Path::new(workspace_path);
+    let sessions: Vec<PiSessionInfo> = sessions
+        .into_iter()
+        .filter(|info| info.cwd.starts_with(workspace))
+        .collect();
+    let busy = sessions.iter().any(|info| {
+        matches!(
+            info.state,
+            PiSessionState::Streaming | PiSessionState::Compacting | PiSessionState::Aborting
+        )
+    });
+    if busy {
+        return None;
+    }
+    Some(
+        sessions
+            .into_iter()
+            .map(|info| HibernatedAgent {
+                session_id: info.session_id,
+                cwd: info.cwd.to_string_lossy().into_owned(),
+                harness: info.harness,
+                provider: info.provider,
+                model: info.model,
+                sandbox_profile: info.sandbox_profile,
+            })
+            .collect(),
+    )
+}
+
 /// Create empty home directory structure.
 fn create_empty_home_dirs(user_home: &std::path::Path) -> Result<()> {
     let dirs = [
@@ -3065,6 +4498,74 @@ fn create_empty_home_dirs(user_home: &std::path::Path) -> Result<()> {
     Ok(())
 }
 
+/// Most extra environment variables a session setup may carry.
+pub const MAX_SETUP_ENV_VARS: usize = 64;
+
+/// Check the extra environment variables of a session setup. Returns a
+/// message for the caller on failure.
+pub fn validate_setup_env(env: &std::collections::HashMap<String, String>) -> Result<(), String> {
+    if env.len() > MAX_SETUP_ENV_VARS {
+        return Err(format!(
+            "at most {} environment variables are allowed",
+            MAX_SETUP_ENV_VARS
+        ));
+    }
+    for (key, value) in env {
+        let valid_name = key
+            .chars()
+            .next()
+            .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+            && key.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+        if !valid_name {
+            return Err(format!("invalid environment variable name '{}'", key));
+        }
+        if value.contains('\0') {
+            return Err(format!("environment variable {} contains a NUL byte", key));
+        }
+    }
+    Ok(())
+}
+
+/// Most extra mounts a session setup may carry.
+pub const MAX_SETUP_MOUNTS: usize = 16;
+
+/// Check the mounts of a session setup without touching the filesystem.
+/// Returns a message for the caller on failure.
+pub fn validate_setup_mounts(mounts: &[SessionMount]) -> Result<(), String> {
+    if mounts.len() > MAX_SETUP_MOUNTS {
+        return Err(format!("at most {} mounts are allowed", MAX_SETUP_MOUNTS));
+    }
+    let mut targets = HashSet::new();
+    for mount in mounts {
+        let target = std::path::Path::new(&mount.target);
+        if mount.source.trim().is_empty() {
+            return Err("mount source must not be empty".to_string());
+        }
+        if !target.is_absolute()
+            || target
+                .components()
+                .any(|c| matches!(c, std::path::Component::ParentDir))
+        {
+            return Err(format!(
+                "mount target {} must be an absolute path",
+                mount.target
+            ));
+        }
+        // The workspace is mounted at /home/dev; mounts go next to it or
+        // below it, never over it.
+        if matches!(
+            mount.target.trim_end_matches('/'),
+            "" | "/home" | "/home/dev"
+        ) {
+            return Err(format!("cannot mount over {}", mount.target));
+        }
+        if !targets.insert(mount.target.trim_end_matches('/')) {
+            return Err(format!("{} is mounted twice", mount.target));
+        }
+    }
+    Ok(())
+}
+
 /// Recursively copy a directory.
 fn copy_dir_recursive(src: &std::path::Path, dst: &std::path::Path) -> Result<()> {
     std::fs::create_dir_all(dst)?;
@@ -3112,6 +4613,12 @@ mod tests {
     #[derive(Default)]
     struct FakeRuntime {
         last_env: Mutex<HashMap<String, String>>,
+        last_resources: Mutex<ResourceLimits>,
+        last_read_only_volumes: Mutex<Vec<(String, String)>>,
+        checkpoints: Mutex<Vec<String>>,
+        restored: Mutex<Vec<String>>,
+        /// Containers created stopped, held and started, in order.
+        lifecycle: Mutex<Vec<&'static str>>,
     }
 
     #[async_trait::async_trait]
@@ -3121,22 +4628,59 @@ mod tests {
             config: &ContainerConfig,
         ) -> crate::container::ContainerResult<String> {
             *self.last_env.lock().t() = config.env.clone();
+            *self.last_resources.lock().t() = config.resources.clone();
+            *self.last_read_only_volumes.lock().t() = config.read_only_volumes.clone();
+
+            Ok("fake-container-id".to_string())
+        }
+
+        async fn stop_container(
+            &self,
+            _container_id: &str,
+            _timeout_seconds: Option<u32>,
+        ) -> crate::container::ContainerResult<()> {
+            Ok(())
+        }
 
+        async fn start_container(
+            &self,
+            _container_id: &str,
+        ) -> crate::container::ContainerResult<()> {
+            self.lifecycle.lock().t().push("start");
+            Ok(())
+        }
+
+        async fn create_stopped_container(
+            &self,
+            _config: &ContainerConfig,
+        ) -> crate::container::ContainerResult<String> {
+            self.lifecycle.lock().t().push("create");
             Ok("fake-container-id".to_string())
         }
 
-        async fn stop_container(
+        async fn hold_container(
+            &self,
+            _container_id: &str,
+        ) -> crate::container::ContainerResult<()> {
+            self.lifecycle.lock().t().push("hold");
+            Ok(())
+        }
+
+        async fn checkpoint_container(
             &self,
             _container_id: &str,
-            _timeout_seconds: Option<u32>,
+            name: &str,
         ) -> crate::container::ContainerResult<()> {
+            self.checkpoints.lock().t().push(name.to_string());
             Ok(())
         }
 
-        async fn start_container(
+        async fn restore_container(
             &self,
             _container_id: &str,
+            name: &str,
         ) -> crate::container::ContainerResult<()> {
+            self.restored.lock().t().push(name.to_string());
             Ok(())
         }
 
@@ -3241,7 +4785,7 @@ mod tests {
     #[tokio::test]
     async fn create_session_never_persists_eavs_virtual_key() {
         let db = Database::in_memory().await.t();
-        let repo = SessionRepository::new(db.pool().clone());
+        let repo = SessionRepository::new(db.shared().clone());
         let fake_runtime = Arc::new(FakeRuntime::default());
         let runtime: Arc<dyn ContainerRuntimeApi> = fake_runtime.clone();
         let eavs: Arc<dyn EavsApi> = Arc::new(FakeEavs);
@@ -3252,6 +4796,7 @@ mod tests {
             base_port: 41820,
             user_data_path: workspace_dir.path().to_string_lossy().to_string(),
             skel_path: None,
+            resource_limits: ResourceLimits::default(),
             default_session_budget_usd: Some(10.0),
             default_session_rpm: Some(60),
             eavs_container_url: Some("http://eavs".to_string()),
@@ -3262,6 +4807,7 @@ mod tests {
             mmry_container_url: None,
             max_concurrent_sessions: SessionService::DEFAULT_MAX_CONCURRENT_SESSIONS,
             idle_timeout_minutes: SessionService::DEFAULT_IDLE_TIMEOUT_MINUTES,
+            idle_action: IdleAction::Stop,
             idle_check_interval_seconds: 5 * 60,
             pi_bridge_enabled: false,
             pi_provider: None,
@@ -3281,6 +4827,7 @@ mod tests {
                 image: None,
                 agent: None,
                 env: Default::default(),
+                sandbox_profile: None,
             })
             .await
             .t();
@@ -3301,10 +4848,468 @@ mod tests {
         );
     }
 
+    #[tokio::test]
+    async fn sandbox_profile_egress_rules_gate_the_container_start() {
+        let db = Database::in_memory().await.t();
+        let repo = SessionRepository::new(db.shared().clone());
+        let fake_runtime = Arc::new(FakeRuntime::default());
+        let runtime: Arc<dyn ContainerRuntimeApi> = fake_runtime.clone();
+        let data_dir = tempfile::tempdir().t();
+        let config = SessionServiceConfig {
+            default_image: "test-image:latest".to_string(),
+            user_data_path: data_dir.path().to_string_lossy().to_string(),
+            runtime_mode: RuntimeMode::Container,
+            ..Default::default()
+        };
+        let service = |egress: ContainerEgress| {
+            let mut service = SessionService::new(repo.clone(), runtime.clone(), config.clone())
+                .with_egress(egress);
+            service.readiness = Arc::new(NoopReadiness);
+            service
+        };
+        let request = |profile: &str| CreateSessionRequest {
+            workspace_path: None,
+            image: None,
+            agent: None,
+            env: Default::default(),
+            sandbox_profile: Some(profile.to_string()),
+        };
+
+        // Without rules the container is created and started as before.
+        let open = service(ContainerEgress::new(oqto_sandbox::SandboxConfig::default()));
+        let session = open
+            .for_user("test")
+            .create_session(request("full"))
+            .await
+            .t();
+        assert_eq!(session.status, SessionStatus::Running);
+        assert!(fake_runtime.lifecycle.lock().t().is_empty());
+
+        let unknown = open
+            .for_user("test")
+            .create_session(request("no-such-profile"))
+            .await;
+        assert!(unknown.is_err());
+
+        // net-off isolates the container: it is held for the rules, and the
+        // fake runtime reports no pid to attach them to, so it never starts.
+        let err = open
+            .for_user("test")
+            .create_session(request("net-off"))
+            .await
+            .expect_err("started without egress rules");
+        assert!(format!("{err:#}").contains("pid"), "{err:#}");
+        assert_eq!(*fake_runtime.lifecycle.lock().t(), ["create", "hold"]);
+
+        // A sandbox.toml that cannot be loaded fails the start.
+        let broken = service(ContainerEgress::unavailable(&anyhow::anyhow!("bad toml")));
+        let err = broken
+            .for_user("test")
+            .create_session(request("full"))
+            .await
+            .expect_err("started without its sandbox config");
+        assert!(format!("{err:#}").contains("bad toml"), "{err:#}");
+    }
+
+    #[tokio::test]
+    async fn clone_session_copies_setup_and_workspace() {
+        let db = Database::in_memory().await.t();
+        let repo = SessionRepository::new(db.shared().clone());
+        let fake_runtime = Arc::new(FakeRuntime::default());
+        let runtime: Arc<dyn ContainerRuntimeApi> = fake_runtime.clone();
+        let eavs: Arc<dyn EavsApi> = Arc::new(FakeEavs);
+        let data_dir = tempfile::tempdir().t();
+
+        let config = SessionServiceConfig {
+            default_image: "test-image:latest".to_string(),
+            user_data_path: data_dir.path().to_string_lossy().to_string(),
+            runtime_mode: RuntimeMode::Container,
+            eavs_container_url: Some("http://eavs".to_string()),
+            pi_model: Some("default-model".to_string()),
+            ..Default::default()
+        };
+        let mut service = SessionService::with_eavs(repo.clone(), runtime, eavs, config);
+        service.readiness = Arc::new(NoopReadiness);
+        let sessions = service.for_user("test");
+
+        let source = sessions
+            .create_session(CreateSessionRequest {
+                workspace_path: None,
+                image: Some("custom-image:1".to_string()),
+                agent: Some("reviewer".to_string()),
+                env: Default::default(),
+                sandbox_profile: None,
+            })
+            .await
+            .t();
+        repo.save_setup(&SessionSetup {
+            session_id: source.id.clone(),
+            env: HashMap::from([("FOO".to_string(), "1".to_string())]),
+            ..Default::default()
+        })
+        .await
+        .t();
+        let notes = std::path::Path::new(&source.workspace_path).join("workspace/notes.txt");
+        std::fs::write(&notes, "hello").t();
+
+        let copy = sessions
+            .clone_session(
+                &source.id,
+                CloneSessionRequest {
+                    workspace: CloneWorkspace::Copy,
+                    model: Some("other-model".to_string()),
+                    env: HashMap::from([
+                        ("BAR".to_string(), "2".to_string()),
+                        ("EAVS_VIRTUAL_KEY".to_string(), "mine".to_string()),
+                    ]),
+                    ..Default::default()
+                },
+            )
+            .await
+            .t();
+        assert_eq!(copy.image, "custom-image:1");
+        assert_eq!(copy.agent.as_deref(), Some("reviewer"));
+        assert_ne!(copy.workspace_path, source.workspace_path);
+        let copied = std::path::Path::new(&copy.workspace_path).join("workspace/notes.txt");
+        assert_eq!(std::fs::read_to_string(copied).t(), "hello");
+
+        let setup = sessions.get_session_setup(&copy.id).await.t().t();
+        assert_eq!(setup.cloned_from.as_deref(), Some(source.id.as_str()));
+        assert_eq!(setup.workspace, Some(CloneWorkspace::Copy));
+        assert_eq!(setup.model.as_deref(), Some("other-model"));
+        assert_eq!(setup.env.get("FOO").map(String::as_str), Some("1"));
+
+        {
+            // Server-set variables win over the setup's.
+            let last_env = fake_runtime.last_env.lock().t();
+            assert_eq!(last_env.get("FOO").map(String::as_str), Some("1"));
+            assert_eq!(last_env.get("BAR").map(String::as_str), Some("2"));
+            assert_eq!(
+                last_env.get("EAVS_VIRTUAL_KEY").map(String::as_str),
+                Some("vk_test_123")
+            );
+        }
+
+        let shared = sessions
+            .clone_session(&copy.id, CloneSessionRequest::default())
+            .await
+            .t();
+        assert_eq!(shared.workspace_path, copy.workspace_path);
+        let setup = sessions.get_session_setup(&shared.id).await.t().t();
+        assert_eq!(setup.model.as_deref(), Some("other-model"));
+        assert_eq!(setup.env.len(), 3);
+
+        assert_eq!(
+            sessions.list_session_clones(&source.id).await.t(),
+            vec![copy.id]
+        );
+        assert!(validate_setup_env(&HashMap::from([("1X".to_string(), String::new())])).is_err());
+    }
+
+    #[tokio::test]
+    async fn configured_session_mounts_directories() {
+        let db = Database::in_memory().await.t();
+        let repo = SessionRepository::new(db.shared().clone());
+        let fake_runtime = Arc::new(FakeRuntime::default());
+        let runtime: Arc<dyn ContainerRuntimeApi> = fake_runtime.clone();
+        let data_dir = tempfile::tempdir().t();
+        let datasets = data_dir.path().join("users/test/datasets");
+        std::fs::create_dir_all(&datasets).t();
+
+        let config = SessionServiceConfig {
+            default_image: "test-image:latest".to_string(),
+            user_data_path: data_dir.path().to_string_lossy().to_string(),
+            runtime_mode: RuntimeMode::Container,
+            ..Default::default()
+        };
+        let mut service = SessionService::new(repo.clone(), runtime, config);
+        service.readiness = Arc::new(NoopReadiness);
+        let sessions = service.for_user("test");
+        let request = || CreateSessionRequest {
+            workspace_path: None,
+            image: None,
+            agent: None,
+            env: Default::default(),
+            sandbox_profile: None,
+        };
+        let mount = |source: &std::path::Path| SessionMount {
+            source: source.to_string_lossy().to_string(),
+            target: "/data".to_string(),
+            read_only: true,
+        };
+
+        let session = sessions
+            .create_configured_session(
+                request(),
+                SessionSetup {
+                    mounts: vec![mount(&datasets)],
+                    ..Default::default()
+                },
+            )
+            .await
+            .t();
+        let source = datasets.canonicalize().t().to_string_lossy().to_string();
+        let token_dir = data_dir.path().join("fileserver-tokens").join(&session.id);
+        assert_eq!(
+            *fake_runtime.last_read_only_volumes.lock().t(),
+            vec![
+                (source.clone(), "/data".to_string()),
+                (
+                    token_dir.display().to_string(),
+                    CONTAINER_FILESERVER_DIR.to_string()
+                ),
+            ]
+        );
+        let setup = sessions.get_session_setup(&session.id).await.t().t();
+        assert_eq!(setup.mounts[0].source, source);
+
+        let outside = tempfile::tempdir().t();
+        let err = sessions
+            .create_configured_session(
+                request(),
+                SessionSetup {
+                    mounts: vec![mount(outside.path())],
+                    ..Default::default()
+                },
+            )
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("outside allowed roots"));
+    }
+
+    #[tokio::test]
+    async fn container_fileserver_secret_is_mounted_and_rotated_on_resume() {
+        let db = Database::in_memory().await.t();
+        let repo = SessionRepository::new(db.shared().clone());
+        let fake_runtime = Arc::new(FakeRuntime::default());
+        let runtime: Arc<dyn ContainerRuntimeApi> = fake_runtime.clone();
+        let data_dir = tempfile::tempdir().t();
+        let config = SessionServiceConfig {
+            default_image: "test-image:latest".to_string(),
+            user_data_path: data_dir.path().to_string_lossy().to_string(),
+            runtime_mode: RuntimeMode::Container,
+            ..Default::default()
+        };
+        let mut service = SessionService::new(repo.clone(), runtime, config);
+        service.readiness = Arc::new(NoopReadiness);
+        let session = service
+            .for_user("test")
+            .create_session(CreateSessionRequest {
+                workspace_path: None,
+                image: None,
+                agent: None,
+                env: Default::default(),
+                sandbox_profile: None,
+            })
+            .await
+            .t();
+
+        {
+            let env = fake_runtime.last_env.lock().t();
+            assert!(!env.contains_key(oqto_files::capability::TOKEN_SECRET_ENV));
+            assert_eq!(
+                env.get(oqto_files::capability::TOKEN_SECRET_FILE_ENV)
+                    .map(String::as_str),
+                Some("/run/oqto/fileserver/token-secret")
+            );
+        }
+        let file = data_dir
+            .path()
+            .join("fileserver-tokens")
+            .join(&session.id)
+            .join(FILESERVER_TOKEN_FILE);
+        let first = std::fs::read_to_string(&file).t();
+        assert_eq!(
+            repo.fileserver_token_secret(&session.id).await.t(),
+            Some(first.clone())
+        );
+
+        service.stop_session(&session.id).await.t();
+        service.resume_session(&session.id).await.t();
+        let second = std::fs::read_to_string(&file).t();
+        assert_ne!(first, second);
+        assert_eq!(
+            repo.fileserver_token_secret(&session.id).await.t(),
+            Some(second)
+        );
+    }
+
+    #[test]
+    fn setup_mounts_are_validated() {
+        let mount = |target: &str| SessionMount {
+            source: "data".to_string(),
+            target: target.to_string(),
+            read_only: false,
+        };
+        assert!(validate_setup_mounts(&[mount("/data"), mount("/home/dev/ref")]).is_ok());
+        assert!(validate_setup_mounts(&[mount("data")]).is_err());
+        assert!(validate_setup_mounts(&[mount("/home/dev/")]).is_err());
+        assert!(validate_setup_mounts(&[mount("/data/../etc")]).is_err());
+        assert!(validate_setup_mounts(&[mount("/data"), mount("/data/")]).is_err());
+    }
+
+    #[tokio::test]
+    async fn create_session_applies_user_resource_limits() {
+        let db = Database::in_memory().await.t();
+        sqlx::query("INSERT INTO users (id, username, email, display_name) VALUES (?, ?, ?, ?)")
+            .bind("alice")
+            .bind("alice")
+            .bind("alice@example.com")
+            .bind("Alice")
+            .execute(db.pool())
+            .await
+            .t();
+        let repo = SessionRepository::new(db.shared().clone());
+        let fake_runtime = Arc::new(FakeRuntime::default());
+        let runtime: Arc<dyn ContainerRuntimeApi> = fake_runtime.clone();
+        let workspace_dir = tempfile::tempdir().t();
+        let config = SessionServiceConfig {
+            user_data_path: workspace_dir.path().to_string_lossy().to_string(),
+            resource_limits: ResourceLimits {
+                cpu_shares: Some(1024),
+                memory_mb: Some(2048),
+                ..Default::default()
+            },
+            ..SessionServiceConfig::default()
+        };
+        let mut service = SessionService::new(repo.clone(), runtime, config);
+        service.readiness = Arc::new(NoopReadiness);
+
+        let limits = service
+            .set_user_resource_limits(
+                "alice",
+                Some(&ResourceLimits {
+                    memory_mb: Some(8192),
+                    gpus: Some("all".to_string()),
+                    ..Default::default()
+                }),
+            )
+            .await
+            .t();
+        let expected = ResourceLimits {
+            cpu_shares: Some(1024),
+            memory_mb: Some(8192),
+            pids_limit: None,
+            gpus: Some("all".to_string()),
+        };
+        assert_eq!(limits.effective, expected);
+        assert!(
+            service
+                .set_user_resource_limits(
+                    "alice",
+                    Some(&ResourceLimits {
+                        memory_mb: Some(1),
+                        ..Default::default()
+                    }),
+                )
+                .await
+                .is_err()
+        );
+
+        let session = service
+            .for_user("alice")
+            .create_session(CreateSessionRequest {
+                workspace_path: None,
+                image: None,
+                agent: None,
+                env: Default::default(),
+                sandbox_profile: None,
+            })
+            .await
+            .t();
+        assert_eq!(session.resource_limits.as_ref(), Some(&expected));
+        assert_eq!(*fake_runtime.last_resources.lock().t(), expected);
+
+        let override_limits = ResourceLimits {
+            pids_limit: Some(512),
+            ..Default::default()
+        };
+        let session = service
+            .set_session_resource_limits(&session.id, &override_limits)
+            .await
+            .t();
+        assert_eq!(session.resource_limits, Some(override_limits));
+
+        let limits = service.set_user_resource_limits("alice", None).await.t();
+        assert!(limits.overrides.is_none());
+        assert_eq!(limits.effective.memory_mb, Some(2048));
+    }
+
+    #[tokio::test]
+    async fn create_session_enforces_quarantine_cap() {
+        use crate::registration::{
+            MailerKind, RegistrationConfig, RegistrationRepository, RegistrationService,
+        };
+
+        let db = Database::in_memory().await.t();
+        sqlx::query("INSERT INTO users (id, username, email, display_name) VALUES (?, ?, ?, ?)")
+            .bind("alice")
+            .bind("alice")
+            .bind("alice@example.com")
+            .bind("Alice")
+            .execute(db.pool())
+            .await
+            .t();
+        let registration = RegistrationService::new(
+            RegistrationRepository::new(db.shared().clone()),
+            RegistrationConfig {
+                open: true,
+                public_url: "https://oqto.example.com".to_string(),
+                mailer: MailerKind::Log,
+                ..Default::default()
+            },
+        )
+        .t();
+        registration
+            .record("alice", "alice@example.com", None)
+            .await
+            .t();
+
+        let repo = SessionRepository::new(db.shared().clone());
+        let runtime: Arc<dyn ContainerRuntimeApi> = Arc::new(FakeRuntime::default());
+        let workspace_dir = tempfile::tempdir().t();
+        let config = SessionServiceConfig {
+            user_data_path: workspace_dir.path().to_string_lossy().to_string(),
+            ..SessionServiceConfig::default()
+        };
+        let mut service =
+            SessionService::new(repo, runtime, config).with_registration(Arc::new(registration));
+        service.readiness = Arc::new(NoopReadiness);
+        let request = || CreateSessionRequest {
+            workspace_path: None,
+            image: None,
+            agent: None,
+            env: Default::default(),
+            sandbox_profile: None,
+        };
+
+        service
+            .for_user("alice")
+            .create_session(request())
+            .await
+            .t();
+        let err = service
+            .for_user("alice")
+            .create_session(request())
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("verify your email address"));
+
+        sqlx::query("UPDATE registrations SET quarantined = 0")
+            .execute(db.pool())
+            .await
+            .t();
+        service
+            .for_user("alice")
+            .create_session(request())
+            .await
+            .t();
+    }
+
     #[tokio::test]
     async fn collect_container_stats_returns_sessions() {
         let db = Database::in_memory().await.t();
-        let repo = SessionRepository::new(db.pool().clone());
+        let repo = SessionRepository::new(db.shared().clone());
         let runtime: Arc<dyn ContainerRuntimeApi> = Arc::new(FakeRuntime::default());
         let service = SessionService::new(repo.clone(), runtime, SessionServiceConfig::default());
 
@@ -3336,6 +5341,9 @@ mod tests {
             stopped_at: None,
             last_activity_at: Some(Utc::now().to_rfc3339()),
             error_message: None,
+            exit_info: None,
+            resource_limits: None,
+            hibernation: None,
         };
 
         repo.create(&session).await.t();
@@ -3373,7 +5381,7 @@ mod tests {
         };
 
         let db = Database::in_memory().await.t();
-        let repo = SessionRepository::new(db.pool().clone());
+        let repo = SessionRepository::new(db.shared().clone());
         let runner = RunnerClient::default();
         let service = SessionService::with_runner(repo, runner, local_runtime, config);
 
@@ -3423,7 +5431,7 @@ mod tests {
     #[tokio::test]
     async fn test_session_service_with_runner_constructor() {
         let db = Database::in_memory().await.t();
-        let repo = SessionRepository::new(db.pool().clone());
+        let repo = SessionRepository::new(db.shared().clone());
 
         let local_config = LocalRuntimeConfig::default();
         let local_runtime = LocalRuntime::new(local_config);
@@ -3446,7 +5454,7 @@ mod tests {
     #[tokio::test]
     async fn test_session_service_with_runner_and_eavs_constructor() {
         let db = Database::in_memory().await.t();
-        let repo = SessionRepository::new(db.pool().clone());
+        let repo = SessionRepository::new(db.shared().clone());
 
         let local_config = LocalRuntimeConfig::default();
         let local_runtime = LocalRuntime::new(local_config);
@@ -3635,7 +5643,7 @@ mod tests {
     #[tokio::test]
     async fn test_resume_session_marks_failed_on_container_start_error() {
         let db = Database::in_memory().await.t();
-        let repo = SessionRepository::new(db.pool().clone());
+        let repo = SessionRepository::new(db.shared().clone());
         let runtime: Arc<dyn ContainerRuntimeApi> = Arc::new(FailingRuntime::new(true));
 
         let config = SessionServiceConfig {
@@ -3677,6 +5685,9 @@ mod tests {
             stopped_at: Some(Utc::now().to_rfc3339()),
             last_activity_at: None,
             error_message: None,
+            exit_info: None,
+            resource_limits: None,
+            hibernation: None,
         };
 
         repo.create(&session).await.t();
@@ -3698,7 +5709,7 @@ mod tests {
     #[tokio::test]
     async fn test_resume_session_marks_failed_on_readiness_timeout() {
         let db = Database::in_memory().await.t();
-        let repo = SessionRepository::new(db.pool().clone());
+        let repo = SessionRepository::new(db.shared().clone());
         let runtime: Arc<dyn ContainerRuntimeApi> = Arc::new(FakeRuntime::default());
 
         let config = SessionServiceConfig {
@@ -3741,6 +5752,9 @@ mod tests {
             stopped_at: Some(Utc::now().to_rfc3339()),
             last_activity_at: None,
             error_message: None,
+            exit_info: None,
+            resource_limits: None,
+            hibernation: None,
         };
 
         repo.create(&session).await.t();
@@ -3761,7 +5775,7 @@ mod tests {
     #[tokio::test]
     async fn test_resume_session_not_stopped_returns_error() {
         let db = Database::in_memory().await.t();
-        let repo = SessionRepository::new(db.pool().clone());
+        let repo = SessionRepository::new(db.shared().clone());
         let runtime: Arc<dyn ContainerRuntimeApi> = Arc::new(FakeRuntime::default());
 
         let config = SessionServiceConfig::default();
@@ -3796,6 +5810,9 @@ mod tests {
             stopped_at: None,
             last_activity_at: None,
             error_message: None,
+            exit_info: None,
+            resource_limits: None,
+            hibernation: None,
         };
 
         repo.create(&session).await.t();
@@ -3810,7 +5827,7 @@ mod tests {
     #[tokio::test]
     async fn test_resume_session_not_found_returns_error() {
         let db = Database::in_memory().await.t();
-        let repo = SessionRepository::new(db.pool().clone());
+        let repo = SessionRepository::new(db.shared().clone());
         let runtime: Arc<dyn ContainerRuntimeApi> = Arc::new(FakeRuntime::default());
 
         let config = SessionServiceConfig::default();
@@ -3826,7 +5843,7 @@ mod tests {
     #[tokio::test]
     async fn test_resume_session_success() {
         let db = Database::in_memory().await.t();
-        let repo = SessionRepository::new(db.pool().clone());
+        let repo = SessionRepository::new(db.shared().clone());
         let runtime: Arc<dyn ContainerRuntimeApi> = Arc::new(FakeRuntime::default());
 
         let config = SessionServiceConfig {
@@ -3868,6 +5885,9 @@ mod tests {
             stopped_at: Some(Utc::now().to_rfc3339()),
             last_activity_at: None,
             error_message: None,
+            exit_info: None,
+            resource_limits: None,
+            hibernation: None,
         };
 
         repo.create(&session).await.t();
@@ -3884,4 +5904,102 @@ mod tests {
         let stored = repo.get("test-session-4").await.t().t();
         assert_eq!(stored.status, SessionStatus::Running);
     }
+
+    #[tokio::test]
+    async fn test_hibernate_and_resume_restores_checkpoint() {
+        let db = Database::in_memory().await.t();
+        let repo = SessionRepository::new(db.shared().clone());
+        let runtime = Arc::new(FakeRuntime::default());
+
+        let config = SessionServiceConfig {
+            default_image: "test-image:latest".to_string(),
+            base_port: 41820,
+            runtime_mode: RuntimeMode::Container,
+            ..Default::default()
+        };
+
+        let mut service = SessionService::new(repo.clone(), runtime.clone(), config);
+        service.readiness = Arc::new(NoopReadiness);
+
+        let session = Session {
+            id: "test-session-5".to_string(),
+            readable_id: None,
+
+            container_id: Some("container-5".to_string()),
+            container_name: "oqto-test-5".to_string(),
+            user_id: "user-1".to_string(),
+            workspace_path: "/tmp/workspace5".to_string(),
+            agent: None,
+            image: "test-image:latest".to_string(),
+            image_digest: None,
+            agent_port: 41833,
+            fileserver_port: 41834,
+            ttyd_port: 41835,
+            eavs_port: None,
+            agent_base_port: None,
+            max_agents: Some(10),
+            eavs_key_id: None,
+            eavs_key_hash: None,
+            eavs_virtual_key: None,
+            mmry_port: None,
+            status: SessionStatus::Running,
+            runtime_mode: RuntimeMode::Container,
+            created_at: Utc::now().to_rfc3339(),
+            started_at: Some(Utc::now().to_rfc3339()),
+            stopped_at: None,
+            last_activity_at: None,
+            error_message: None,
+            exit_info: None,
+            resource_limits: None,
+            hibernation: None,
+        };
+
+        repo.create(&session).await.t();
+
+        service.hibernate_session("test-session-5").await.t();
+        let stored = repo.get("test-session-5").await.t().t();
+        assert_eq!(stored.status, SessionStatus::Stopped);
+        let checkpoint = stored.hibernation.t().checkpoint.t();
+        assert_eq!(*runtime.checkpoints.lock().t(), vec![checkpoint.clone()]);
+
+        let resumed = service.resume_session("test-session-5").await.t();
+        assert_eq!(resumed.status, SessionStatus::Running);
+        assert!(resumed.hibernation.is_none());
+        assert_eq!(*runtime.restored.lock().t(), vec![checkpoint]);
+    }
+
+    #[test]
+    fn test_workspace_agents() {
+        let agent = |id: &str, cwd: &str, state: PiSessionState| PiSessionInfo {
+            session_id: id.to_string(),
+            hstry_id: None,
+            state,
+            last_activity: 0,
+            subscriber_count: 0,
+            cwd: PathBuf::from(cwd),
+            provider: Some("anthropic".to_string()),
+            model: None,
+            harness: None,
+            sandbox_profile: None,
+        };
+
+        let agents = workspace_agents(
+            vec![
+                agent("a", "/home/u/work/project", PiSessionState::Idle),
+                agent("b", "/home/u/other", PiSessionState::Streaming),
+            ],
+            "/home/u/work",
+        )
+        .t();
+        assert_eq!(agents.len(), 1);
+        assert_eq!(agents[0].session_id, "a");
+        assert_eq!(agents[0].cwd, "/home/u/work/project");
+        assert_eq!(agents[0].provider.as_deref(), Some("anthropic"));
+
+        let busy = workspace_agents(
+            vec![agent("a", "/home/u/work", PiSessionState::Streaming)],
+            "/home/u/work",
+        );
+        assert!(busy.is_none());
+    }
 }
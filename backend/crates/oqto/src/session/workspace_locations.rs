@@ -1,6 +1,8 @@
 use anyhow::{Context, Result};
 use serde::{Deserialize, Serialize};
-use sqlx::{FromRow, SqlitePool};
+use sqlx::FromRow;
+
+use crate::db::{self, DbPool, on_pool};
 
 #[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
 pub struct WorkspaceLocation {
@@ -32,11 +34,11 @@ pub struct WorkspaceLocationInput {
 
 #[derive(Debug, Clone)]
 pub struct WorkspaceLocationRepository {
-    pool: SqlitePool,
+    pool: DbPool,
 }
 
 impl WorkspaceLocationRepository {
-    pub fn new(pool: SqlitePool) -> Self {
+    pub fn new(pool: DbPool) -> Self {
         Self { pool }
     }
 
@@ -45,19 +47,19 @@ impl WorkspaceLocationRepository {
         user_id: &str,
         workspace_id: &str,
     ) -> Result<Vec<WorkspaceLocation>> {
-        let locations = sqlx::query_as::<_, WorkspaceLocation>(
+        let locations = on_pool!(&self.pool, |pool| sqlx::query_as::<_, WorkspaceLocation>(
             r#"
             SELECT id, user_id, workspace_id, location_id, kind, path, runner_id, repo_fingerprint,
                    is_active, created_at, updated_at
             FROM workspace_locations
-            WHERE user_id = ? AND workspace_id = ?
+            WHERE user_id = $1 AND workspace_id = $2
             ORDER BY created_at ASC
             "#,
         )
         .bind(user_id)
         .bind(workspace_id)
-        .fetch_all(&self.pool)
-        .await
+        .fetch_all(pool)
+        .await)
         .context("listing workspace locations")?;
 
         Ok(locations)
@@ -68,19 +70,19 @@ impl WorkspaceLocationRepository {
         user_id: &str,
         workspace_id: &str,
     ) -> Result<Option<WorkspaceLocation>> {
-        let location = sqlx::query_as::<_, WorkspaceLocation>(
+        let location = on_pool!(&self.pool, |pool| sqlx::query_as::<_, WorkspaceLocation>(
             r#"
             SELECT id, user_id, workspace_id, location_id, kind, path, runner_id, repo_fingerprint,
                    is_active, created_at, updated_at
             FROM workspace_locations
-            WHERE user_id = ? AND workspace_id = ? AND is_active = 1
+            WHERE user_id = $1 AND workspace_id = $2 AND is_active = 1
             LIMIT 1
             "#,
         )
         .bind(user_id)
         .bind(workspace_id)
-        .fetch_optional(&self.pool)
-        .await
+        .fetch_optional(pool)
+        .await)
         .context("fetching active workspace location")?;
 
         Ok(location)
@@ -92,20 +94,20 @@ impl WorkspaceLocationRepository {
         workspace_id: &str,
         location_id: &str,
     ) -> Result<Option<WorkspaceLocation>> {
-        let location = sqlx::query_as::<_, WorkspaceLocation>(
+        let location = on_pool!(&self.pool, |pool| sqlx::query_as::<_, WorkspaceLocation>(
             r#"
             SELECT id, user_id, workspace_id, location_id, kind, path, runner_id, repo_fingerprint,
                    is_active, created_at, updated_at
             FROM workspace_locations
-            WHERE user_id = ? AND workspace_id = ? AND location_id = ?
+            WHERE user_id = $1 AND workspace_id = $2 AND location_id = $3
             LIMIT 1
             "#,
         )
         .bind(user_id)
         .bind(workspace_id)
         .bind(location_id)
-        .fetch_optional(&self.pool)
-        .await
+        .fetch_optional(pool)
+        .await)
         .context("fetching workspace location")?;
 
         Ok(location)
@@ -116,53 +118,58 @@ impl WorkspaceLocationRepository {
         location: &WorkspaceLocationInput,
         set_active: bool,
     ) -> Result<()> {
-        let mut tx = self.pool.begin().await?;
+        let now = db::now();
+        on_pool!(&self.pool, |pool| {
+            let mut tx = pool.begin().await?;
+
+            if set_active {
+                sqlx::query(
+                    r#"
+                    UPDATE workspace_locations
+                    SET is_active = 0, updated_at = $1
+                    WHERE user_id = $2 AND workspace_id = $3
+                    "#,
+                )
+                .bind(&now)
+                .bind(&location.user_id)
+                .bind(&location.workspace_id)
+                .execute(&mut *tx)
+                .await
+                .context("clearing active workspace location")?;
+            }
 
-        if set_active {
             sqlx::query(
                 r#"
-                UPDATE workspace_locations
-                SET is_active = 0, updated_at = datetime('now')
-                WHERE user_id = ? AND workspace_id = ?
+                INSERT INTO workspace_locations (
+                    id, user_id, workspace_id, location_id, kind, path, runner_id, repo_fingerprint,
+                    is_active, created_at, updated_at
+                ) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $10)
+                ON CONFLICT(user_id, workspace_id, location_id)
+                DO UPDATE SET
+                    kind = excluded.kind,
+                    path = excluded.path,
+                    runner_id = excluded.runner_id,
+                    repo_fingerprint = excluded.repo_fingerprint,
+                    is_active = excluded.is_active,
+                    updated_at = excluded.updated_at
                 "#,
             )
+            .bind(&location.id)
             .bind(&location.user_id)
             .bind(&location.workspace_id)
+            .bind(&location.location_id)
+            .bind(&location.kind)
+            .bind(&location.path)
+            .bind(&location.runner_id)
+            .bind(&location.repo_fingerprint)
+            .bind(location.is_active)
+            .bind(&now)
             .execute(&mut *tx)
             .await
-            .context("clearing active workspace location")?;
-        }
+            .context("upserting workspace location")?;
 
-        sqlx::query(
-            r#"
-            INSERT INTO workspace_locations (
-                id, user_id, workspace_id, location_id, kind, path, runner_id, repo_fingerprint,
-                is_active, created_at, updated_at
-            ) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, datetime('now'), datetime('now'))
-            ON CONFLICT(user_id, workspace_id, location_id)
-            DO UPDATE SET
-                kind = excluded.kind,
-                path = excluded.path,
-                runner_id = excluded.runner_id,
-                repo_fingerprint = excluded.repo_fingerprint,
-                is_active = excluded.is_active,
-                updated_at = datetime('now')
-            "#,
-        )
-        .bind(&location.id)
-        .bind(&location.user_id)
-        .bind(&location.workspace_id)
-        .bind(&location.location_id)
-        .bind(&location.kind)
-        .bind(&location.path)
-        .bind(&location.runner_id)
-        .bind(&location.repo_fingerprint)
-        .bind(location.is_active)
-        .execute(&mut *tx)
-        .await
-        .context("upserting workspace location")?;
-
-        tx.commit().await?;
+            tx.commit().await?;
+        });
 
         Ok(())
     }
@@ -173,36 +180,41 @@ impl WorkspaceLocationRepository {
         workspace_id: &str,
         location_id: &str,
     ) -> Result<()> {
-        let mut tx = self.pool.begin().await?;
+        let now = db::now();
+        on_pool!(&self.pool, |pool| {
+            let mut tx = pool.begin().await?;
 
-        sqlx::query(
-            r#"
-            UPDATE workspace_locations
-            SET is_active = 0, updated_at = datetime('now')
-            WHERE user_id = ? AND workspace_id = ?
-            "#,
-        )
-        .bind(user_id)
-        .bind(workspace_id)
-        .execute(&mut *tx)
-        .await
-        .context("clearing active workspace location")?;
+            sqlx::query(
+                r#"
+                UPDATE workspace_locations
+                SET is_active = 0, updated_at = $1
+                WHERE user_id = $2 AND workspace_id = $3
+                "#,
+            )
+            .bind(&now)
+            .bind(user_id)
+            .bind(workspace_id)
+            .execute(&mut *tx)
+            .await
+            .context("clearing active workspace location")?;
 
-        sqlx::query(
-            r#"
-            UPDATE workspace_locations
-            SET is_active = 1, updated_at = datetime('now')
-            WHERE user_id = ? AND workspace_id = ? AND location_id = ?
-            "#,
-        )
-        .bind(user_id)
-        .bind(workspace_id)
-        .bind(location_id)
-        .execute(&mut *tx)
-        .await
-        .context("setting active workspace location")?;
+            sqlx::query(
+                r#"
+                UPDATE workspace_locations
+                SET is_active = 1, updated_at = $1
+                WHERE user_id = $2 AND workspace_id = $3 AND location_id = $4
+                "#,
+            )
+            .bind(&now)
+            .bind(user_id)
+            .bind(workspace_id)
+            .bind(location_id)
+            .execute(&mut *tx)
+            .await
+            .context("setting active workspace location")?;
 
-        tx.commit().await?;
+            tx.commit().await?;
+        });
 
         Ok(())
     }
@@ -0,0 +1,16 @@
+//! Draft sessions: configure a session now, start it later.
+//!
+//! A draft carries everything a session is created with (workspace, image,
+//! agent, model, environment, mounts) plus an optional first prompt. It
+//! allocates no ports or container, so drafts do not count against session
+//! limits. The owner can share a draft with teammates for review (read
+//! only) and start it by hand or by setting `start_at`; the draft then
+//! records the session it became.
+
+mod models;
+mod repository;
+
+pub use models::{
+    DraftShare, DraftStatus, SaveSessionDraftRequest, SessionDraft, ShareSessionDraftRequest,
+};
+pub use repository::SessionDraftRepository;
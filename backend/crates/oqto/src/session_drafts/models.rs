@@ -0,0 +1,113 @@
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::session::SessionMount;
+
+/// Where a draft is in its life.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DraftStatus {
+    /// Being configured; can be edited and started.
+    Draft,
+    /// Its session is being created.
+    Starting,
+    /// Became a session (`session_id`).
+    Started,
+    /// Starting failed (`error`); can be edited and started again.
+    Failed,
+}
+
+impl DraftStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            DraftStatus::Draft => "draft",
+            DraftStatus::Starting => "starting",
+            DraftStatus::Started => "started",
+            DraftStatus::Failed => "failed",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "draft" => Some(DraftStatus::Draft),
+            "starting" => Some(DraftStatus::Starting),
+            "started" => Some(DraftStatus::Started),
+            "failed" => Some(DraftStatus::Failed),
+            _ => None,
+        }
+    }
+
+    /// Whether the draft can still be edited and started.
+    pub fn is_open(&self) -> bool {
+        matches!(self, DraftStatus::Draft | DraftStatus::Failed)
+    }
+}
+
+/// A session configured ahead of time.
+#[derive(Debug, Clone, Serialize)]
+pub struct SessionDraft {
+    pub id: String,
+    pub user_id: String,
+    pub name: Option<String>,
+    pub workspace_path: Option<String>,
+    pub image: Option<String>,
+    pub agent: Option<String>,
+    pub provider: Option<String>,
+    pub model: Option<String>,
+    pub env: HashMap<String, String>,
+    pub mounts: Vec<SessionMount>,
+    /// Sent to the agent once the session is up.
+    pub prompt: Option<String>,
+    /// When the draft starts by itself (UTC).
+    pub start_at: Option<String>,
+    pub status: DraftStatus,
+    /// Session the draft became.
+    pub session_id: Option<String>,
+    /// Agent session the prompt was sent to.
+    pub chat_session_id: Option<String>,
+    /// Why the last start failed.
+    pub error: Option<String>,
+    pub created_at: String,
+    pub updated_at: String,
+}
+
+/// Request body for creating or replacing a draft.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct SaveSessionDraftRequest {
+    #[serde(default)]
+    pub name: Option<String>,
+    #[serde(default)]
+    pub workspace_path: Option<String>,
+    #[serde(default)]
+    pub image: Option<String>,
+    #[serde(default)]
+    pub agent: Option<String>,
+    #[serde(default)]
+    pub provider: Option<String>,
+    #[serde(default)]
+    pub model: Option<String>,
+    #[serde(default)]
+    pub env: HashMap<String, String>,
+    #[serde(default)]
+    pub mounts: Vec<SessionMount>,
+    #[serde(default)]
+    pub prompt: Option<String>,
+    /// RFC 3339 time to start the draft at.
+    #[serde(default)]
+    pub start_at: Option<String>,
+}
+
+/// A reviewer of a draft.
+#[derive(Debug, Clone, Serialize)]
+pub struct DraftShare {
+    pub draft_id: String,
+    pub user_id: String,
+    pub created_at: String,
+}
+
+/// Request body for sharing a draft with a reviewer.
+#[derive(Debug, Clone, Deserialize)]
+pub struct ShareSessionDraftRequest {
+    pub user_id: String,
+}
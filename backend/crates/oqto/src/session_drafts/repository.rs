@@ -0,0 +1,508 @@
+use anyhow::{Context, Result};
+use sqlx::FromRow;
+
+use crate::db::{self, DbPool, on_pool};
+
+use super::{DraftShare, DraftStatus, SaveSessionDraftRequest, SessionDraft};
+
+const DRAFT_COLUMNS: &str = "id, user_id, name, workspace_path, image, agent, provider, model, \
+     env, mounts, prompt, start_at, status, session_id, chat_session_id, error, created_at, updated_at";
+
+#[derive(Debug, Clone, FromRow)]
+struct DraftRow {
+    id: String,
+    user_id: String,
+    name: Option<String>,
+    workspace_path: Option<String>,
+    image: Option<String>,
+    agent: Option<String>,
+    provider: Option<String>,
+    model: Option<String>,
+    env: String,
+    mounts: String,
+    prompt: Option<String>,
+    start_at: Option<String>,
+    status: String,
+    session_id: Option<String>,
+    chat_session_id: Option<String>,
+    error: Option<String>,
+    created_at: String,
+    updated_at: String,
+}
+
+impl From<DraftRow> for SessionDraft {
+    fn from(row: DraftRow) -> Self {
+        Self {
+            id: row.id,
+            user_id: row.user_id,
+            name: row.name,
+            workspace_path: row.workspace_path,
+            image: row.image,
+            agent: row.agent,
+            provider: row.provider,
+            model: row.model,
+            env: serde_json::from_str(&row.env).unwrap_or_default(),
+            mounts: serde_json::from_str(&row.mounts).unwrap_or_default(),
+            prompt: row.prompt,
+            start_at: row.start_at,
+            // The column is constrained to the known values.
+            status: DraftStatus::parse(&row.status).unwrap_or(DraftStatus::Draft),
+            session_id: row.session_id,
+            chat_session_id: row.chat_session_id,
+            error: row.error,
+            created_at: row.created_at,
+            updated_at: row.updated_at,
+        }
+    }
+}
+
+#[derive(Debug, Clone, FromRow)]
+struct DraftShareRow {
+    draft_id: String,
+    user_id: String,
+    created_at: String,
+}
+
+impl From<DraftShareRow> for DraftShare {
+    fn from(row: DraftShareRow) -> Self {
+        Self {
+            draft_id: row.draft_id,
+            user_id: row.user_id,
+            created_at: row.created_at,
+        }
+    }
+}
+
+/// `start_at` in the stored timestamp format, so due drafts can be found
+/// by comparing strings.
+fn stored_start_at(start_at: Option<&str>) -> Result<Option<String>> {
+    start_at
+        .map(|value| {
+            db::parse_timestamp(value)
+                .map(db::timestamp)
+                .with_context(|| format!("invalid start_at '{value}'"))
+        })
+        .transpose()
+}
+
+#[derive(Debug, Clone)]
+pub struct SessionDraftRepository {
+    pool: DbPool,
+}
+
+impl SessionDraftRepository {
+    pub fn new(pool: DbPool) -> Self {
+        Self { pool }
+    }
+
+    pub async fn create(
+        &self,
+        user_id: &str,
+        request: &SaveSessionDraftRequest,
+    ) -> Result<SessionDraft> {
+        let id = format!("draft_{}", uuid::Uuid::new_v4().simple());
+        let now = db::now();
+        let env = serde_json::to_string(&request.env)?;
+        let mounts = serde_json::to_string(&request.mounts)?;
+        let start_at = stored_start_at(request.start_at.as_deref())?;
+        on_pool!(&self.pool, |pool| sqlx::query(
+            r#"INSERT INTO session_drafts
+                   (id, user_id, name, workspace_path, image, agent, provider, model,
+                    env, mounts, prompt, start_at, status, created_at, updated_at)
+               VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, 'draft', $13, $13)"#
+        )
+        .bind(&id)
+        .bind(user_id)
+        .bind(&request.name)
+        .bind(&request.workspace_path)
+        .bind(&request.image)
+        .bind(&request.agent)
+        .bind(&request.provider)
+        .bind(&request.model)
+        .bind(&env)
+        .bind(&mounts)
+        .bind(&request.prompt)
+        .bind(&start_at)
+        .bind(&now)
+        .execute(pool)
+        .await)
+        .context("create session draft")?;
+
+        self.get(&id)
+            .await?
+            .ok_or_else(|| anyhow::anyhow!("Session draft not found after insert"))
+    }
+
+    /// Replace the configuration of an open draft (a failed one becomes a
+    /// draft again). None if the draft is not the user's or no longer open.
+    pub async fn update(
+        &self,
+        id: &str,
+        user_id: &str,
+        request: &SaveSessionDraftRequest,
+    ) -> Result<Option<SessionDraft>> {
+        let env = serde_json::to_string(&request.env)?;
+        let mounts = serde_json::to_string(&request.mounts)?;
+        let start_at = stored_start_at(request.start_at.as_deref())?;
+        let updated = on_pool!(&self.pool, |pool| sqlx::query(
+            r#"UPDATE session_drafts SET
+                   name = $3, workspace_path = $4, image = $5, agent = $6, provider = $7,
+                   model = $8, env = $9, mounts = $10, prompt = $11, start_at = $12,
+                   status = 'draft', error = NULL, updated_at = $13
+               WHERE id = $1 AND user_id = $2 AND status IN ('draft', 'failed')"#
+        )
+        .bind(id)
+        .bind(user_id)
+        .bind(&request.name)
+        .bind(&request.workspace_path)
+        .bind(&request.image)
+        .bind(&request.agent)
+        .bind(&request.provider)
+        .bind(&request.model)
+        .bind(&env)
+        .bind(&mounts)
+        .bind(&request.prompt)
+        .bind(&start_at)
+        .bind(db::now())
+        .execute(pool)
+        .await
+        .map(|r| r.rows_affected()))
+        .context("update session draft")?;
+        if updated == 0 {
+            return Ok(None);
+        }
+        self.get(id).await
+    }
+
+    pub async fn get(&self, id: &str) -> Result<Option<SessionDraft>> {
+        let sql = format!("SELECT {DRAFT_COLUMNS} FROM session_drafts WHERE id = $1");
+        let row = on_pool!(&self.pool, |pool| sqlx::query_as::<_, DraftRow>(&sql)
+            .bind(id)
+            .fetch_optional(pool)
+            .await)
+        .context("get session draft")?;
+        Ok(row.map(Into::into))
+    }
+
+    /// The user's drafts, newest first.
+    pub async fn list_for_user(&self, user_id: &str) -> Result<Vec<SessionDraft>> {
+        let sql = format!(
+            "SELECT {DRAFT_COLUMNS} FROM session_drafts WHERE user_id = $1 ORDER BY created_at DESC, id"
+        );
+        let rows = on_pool!(&self.pool, |pool| sqlx::query_as::<_, DraftRow>(&sql)
+            .bind(user_id)
+            .fetch_all(pool)
+            .await)
+        .context("list session drafts")?;
+        Ok(rows.into_iter().map(Into::into).collect())
+    }
+
+    /// Drafts other users shared with `user_id` for review, newest first.
+    pub async fn list_shared_with(&self, user_id: &str) -> Result<Vec<SessionDraft>> {
+        let sql = format!(
+            "SELECT {DRAFT_COLUMNS} FROM session_drafts WHERE id IN \
+             (SELECT draft_id FROM session_draft_shares WHERE user_id = $1) \
+             ORDER BY created_at DESC, id"
+        );
+        let rows = on_pool!(&self.pool, |pool| sqlx::query_as::<_, DraftRow>(&sql)
+            .bind(user_id)
+            .fetch_all(pool)
+            .await)
+        .context("list session drafts shared with user")?;
+        Ok(rows.into_iter().map(Into::into).collect())
+    }
+
+    pub async fn delete(&self, id: &str, user_id: &str) -> Result<bool> {
+        let deleted = on_pool!(&self.pool, |pool| sqlx::query(
+            "DELETE FROM session_drafts WHERE id = $1 AND user_id = $2"
+        )
+        .bind(id)
+        .bind(user_id)
+        .execute(pool)
+        .await
+        .map(|r| r.rows_affected()))
+        .context("delete session draft")?;
+        Ok(deleted > 0)
+    }
+
+    /// Let `user_id` review a draft.
+    pub async fn share(&self, id: &str, user_id: &str) -> Result<DraftShare> {
+        on_pool!(&self.pool, |pool| sqlx::query(
+            r#"INSERT INTO session_draft_shares (draft_id, user_id, created_at)
+               VALUES ($1, $2, $3)
+               ON CONFLICT (draft_id, user_id) DO NOTHING"#
+        )
+        .bind(id)
+        .bind(user_id)
+        .bind(db::now())
+        .execute(pool)
+        .await)
+        .context("share session draft")?;
+
+        let row = on_pool!(&self.pool, |pool| sqlx::query_as::<_, DraftShareRow>(
+            "SELECT draft_id, user_id, created_at FROM session_draft_shares WHERE draft_id = $1 AND user_id = $2"
+        )
+        .bind(id)
+        .bind(user_id)
+        .fetch_one(pool)
+        .await)
+        .context("get session draft share")?;
+        Ok(row.into())
+    }
+
+    pub async fn unshare(&self, id: &str, user_id: &str) -> Result<bool> {
+        let deleted = on_pool!(&self.pool, |pool| sqlx::query(
+            "DELETE FROM session_draft_shares WHERE draft_id = $1 AND user_id = $2"
+        )
+        .bind(id)
+        .bind(user_id)
+        .execute(pool)
+        .await
+        .map(|r| r.rows_affected()))
+        .context("unshare session draft")?;
+        Ok(deleted > 0)
+    }
+
+    pub async fn list_shares(&self, id: &str) -> Result<Vec<DraftShare>> {
+        let rows = on_pool!(&self.pool, |pool| sqlx::query_as::<_, DraftShareRow>(
+            "SELECT draft_id, user_id, created_at FROM session_draft_shares WHERE draft_id = $1 ORDER BY created_at, user_id"
+        )
+        .bind(id)
+        .fetch_all(pool)
+        .await)
+        .context("list session draft shares")?;
+        Ok(rows.into_iter().map(Into::into).collect())
+    }
+
+    pub async fn is_shared_with(&self, id: &str, user_id: &str) -> Result<bool> {
+        let count: i64 = on_pool!(&self.pool, |pool| sqlx::query_scalar(
+            "SELECT COUNT(*) FROM session_draft_shares WHERE draft_id = $1 AND user_id = $2"
+        )
+        .bind(id)
+        .bind(user_id)
+        .fetch_one(pool)
+        .await)
+        .context("count session draft shares")?;
+        Ok(count > 0)
+    }
+
+    /// Mark an open draft as starting. False if it is not open, e.g.
+    /// because a concurrent start claimed it first.
+    pub async fn claim(&self, id: &str) -> Result<bool> {
+        let claimed = on_pool!(&self.pool, |pool| sqlx::query(
+            r#"UPDATE session_drafts SET status = 'starting', error = NULL, updated_at = $2
+               WHERE id = $1 AND status IN ('draft', 'failed')"#
+        )
+        .bind(id)
+        .bind(db::now())
+        .execute(pool)
+        .await
+        .map(|r| r.rows_affected()))
+        .context("claim session draft")?;
+        Ok(claimed > 0)
+    }
+
+    pub async fn mark_started(
+        &self,
+        id: &str,
+        session_id: &str,
+        chat_session_id: Option<&str>,
+    ) -> Result<()> {
+        on_pool!(&self.pool, |pool| sqlx::query(
+            r#"UPDATE session_drafts SET
+                   status = 'started', session_id = $2, chat_session_id = $3, updated_at = $4
+               WHERE id = $1"#
+        )
+        .bind(id)
+        .bind(session_id)
+        .bind(chat_session_id)
+        .bind(db::now())
+        .execute(pool)
+        .await)
+        .context("mark session draft started")?;
+        Ok(())
+    }
+
+    pub async fn mark_failed(&self, id: &str, error: &str) -> Result<()> {
+        on_pool!(&self.pool, |pool| sqlx::query(
+            "UPDATE session_drafts SET status = 'failed', error = $2, updated_at = $3 WHERE id = $1"
+        )
+        .bind(id)
+        .bind(error)
+        .bind(db::now())
+        .execute(pool)
+        .await)
+        .context("mark session draft failed")?;
+        Ok(())
+    }
+
+    /// Fail drafts left starting by a restart so they can be started again.
+    pub async fn fail_interrupted(&self) -> Result<u64> {
+        on_pool!(&self.pool, |pool| sqlx::query(
+            r#"UPDATE session_drafts SET
+                   status = 'failed', error = 'Interrupted by a server restart', updated_at = $1
+               WHERE status = 'starting'"#
+        )
+        .bind(db::now())
+        .execute(pool)
+        .await
+        .map(|r| r.rows_affected()))
+        .context("fail interrupted session drafts")
+    }
+
+    /// Drafts whose `start_at` has passed. Failed drafts are not retried.
+    pub async fn due(&self, now: chrono::DateTime<chrono::Utc>) -> Result<Vec<SessionDraft>> {
+        let sql = format!(
+            "SELECT {DRAFT_COLUMNS} FROM session_drafts \
+             WHERE status = 'draft' AND start_at IS NOT NULL AND start_at <= $1 ORDER BY start_at"
+        );
+        let rows = on_pool!(&self.pool, |pool| sqlx::query_as::<_, DraftRow>(&sql)
+            .bind(db::timestamp(now))
+            .fetch_all(pool)
+            .await)
+        .context("list due session drafts")?;
+        Ok(rows.into_iter().map(Into::into).collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::Database;
+    use crate::session::SessionMount;
+
+    async fn repo() -> SessionDraftRepository {
+        let db = Database::in_memory().await.unwrap();
+        for user in ["alice", "bob"] {
+            sqlx::query(
+                "INSERT INTO users (id, username, email, display_name) VALUES (?, ?, ?, ?)",
+            )
+            .bind(user)
+            .bind(user)
+            .bind(format!("{user}@example.com"))
+            .bind(user)
+            .execute(db.pool())
+            .await
+            .unwrap();
+        }
+        SessionDraftRepository::new(db.shared().clone())
+    }
+
+    #[tokio::test]
+    async fn test_draft_lifecycle() {
+        let repo = repo().await;
+        let request = SaveSessionDraftRequest {
+            name: Some("nightly".to_string()),
+            env: [("MODE".to_string(), "ci".to_string())].into(),
+            mounts: vec![SessionMount {
+                source: "datasets".to_string(),
+                target: "/data".to_string(),
+                read_only: true,
+            }],
+            prompt: Some("Run the benchmarks".to_string()),
+            start_at: Some("2026-06-08T10:00:00+02:00".to_string()),
+            ..Default::default()
+        };
+        let draft = repo.create("alice", &request).await.unwrap();
+        assert_eq!(draft.status, DraftStatus::Draft);
+        assert_eq!(draft.env["MODE"], "ci");
+        assert!(draft.mounts[0].read_only);
+        assert_eq!(draft.start_at.as_deref(), Some("2026-06-08 08:00:00"));
+
+        let before = db::parse_timestamp("2026-06-08T07:59:59Z").unwrap();
+        let after = db::parse_timestamp("2026-06-08T08:00:00Z").unwrap();
+        assert!(repo.due(before).await.unwrap().is_empty());
+        assert_eq!(repo.due(after).await.unwrap()[0].id, draft.id);
+
+        // Only the owner edits.
+        assert!(
+            repo.update(&draft.id, "bob", &request)
+                .await
+                .unwrap()
+                .is_none()
+        );
+
+        assert!(repo.claim(&draft.id).await.unwrap());
+        assert!(!repo.claim(&draft.id).await.unwrap());
+        assert!(repo.due(after).await.unwrap().is_empty());
+        assert!(
+            repo.update(&draft.id, "alice", &request)
+                .await
+                .unwrap()
+                .is_none()
+        );
+
+        repo.mark_failed(&draft.id, "no runner").await.unwrap();
+        let failed = repo.get(&draft.id).await.unwrap().unwrap();
+        assert_eq!(failed.status, DraftStatus::Failed);
+        assert_eq!(failed.error.as_deref(), Some("no runner"));
+        // Editing a failed draft reopens it.
+        let reopened = repo
+            .update(&draft.id, "alice", &request)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(reopened.status, DraftStatus::Draft);
+        assert!(reopened.error.is_none());
+
+        assert!(repo.claim(&draft.id).await.unwrap());
+        repo.mark_started(&draft.id, "ses_1", Some("chat_1"))
+            .await
+            .unwrap();
+        let started = repo.get(&draft.id).await.unwrap().unwrap();
+        assert_eq!(started.status, DraftStatus::Started);
+        assert_eq!(started.session_id.as_deref(), Some("ses_1"));
+        assert!(!repo.claim(&draft.id).await.unwrap());
+
+        assert!(
+            repo.create(
+                "alice",
+                &SaveSessionDraftRequest {
+                    start_at: Some("tomorrow".to_string()),
+                    ..Default::default()
+                }
+            )
+            .await
+            .is_err()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_draft_sharing() {
+        let repo = repo().await;
+        let draft = repo
+            .create("alice", &SaveSessionDraftRequest::default())
+            .await
+            .unwrap();
+        assert!(!repo.is_shared_with(&draft.id, "bob").await.unwrap());
+
+        repo.share(&draft.id, "bob").await.unwrap();
+        repo.share(&draft.id, "bob").await.unwrap();
+        assert!(repo.is_shared_with(&draft.id, "bob").await.unwrap());
+        assert_eq!(repo.list_shares(&draft.id).await.unwrap().len(), 1);
+        assert_eq!(repo.list_shared_with("bob").await.unwrap()[0].id, draft.id);
+        assert!(repo.list_for_user("bob").await.unwrap().is_empty());
+
+        assert!(repo.unshare(&draft.id, "bob").await.unwrap());
+        assert!(!repo.unshare(&draft.id, "bob").await.unwrap());
+
+        repo.share(&draft.id, "bob").await.unwrap();
+        assert!(!repo.delete(&draft.id, "bob").await.unwrap());
+        assert!(repo.delete(&draft.id, "alice").await.unwrap());
+        assert!(repo.list_shared_with("bob").await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_interrupted_drafts_fail() {
+        let repo = repo().await;
+        let draft = repo
+            .create("alice", &SaveSessionDraftRequest::default())
+            .await
+            .unwrap();
+        assert!(repo.claim(&draft.id).await.unwrap());
+        assert_eq!(repo.fail_interrupted().await.unwrap(), 1);
+        let draft = repo.get(&draft.id).await.unwrap().unwrap();
+        assert_eq!(draft.status, DraftStatus::Failed);
+        assert!(draft.status.is_open());
+    }
+}
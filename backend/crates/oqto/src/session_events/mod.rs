@@ -0,0 +1,353 @@
+//! Cursor-addressed agent event buffers for long-polling clients.
+//!
+//! Some embedded clients cannot hold a WebSocket or SSE connection. For
+//! them, the backend keeps one runner subscription per polled session and
+//! records the canonical events it yields into a bounded
+//! [`EventLog`]. Every event gets a monotonically increasing `seq`; clients
+//! pass the last `next_cursor` back to receive what happened since. The feed shuts down
+//! (and the ring is dropped) once nobody has polled for `idle_timeout_secs`.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+use tokio::sync::Notify;
+use tracing::{debug, info};
+
+use oqto_protocol::events::Event;
+use oqto_protocol::replay::{EventLog, Replay};
+use oqto_runner::client::{PiSubscriptionEvent, RunnerClient};
+
+/// Long-poll compatibility API configuration.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LongPollConfig {
+    /// Serve `GET /api/sessions/{id}/events/poll`.
+    pub enabled: bool,
+    /// Events retained per polled session.
+    pub buffer_events: usize,
+    /// Wait used when the request has no `wait` parameter.
+    pub default_wait_secs: u64,
+    /// Upper bound for `wait`.
+    pub max_wait_secs: u64,
+    /// Most events returned by one poll.
+    pub max_batch: usize,
+    /// Drop a session's feed after this long without polls.
+    pub idle_timeout_secs: u64,
+}
+
+impl Default for LongPollConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            buffer_events: 2000,
+            default_wait_secs: 25,
+            max_wait_secs: 60,
+            max_batch: 500,
+            idle_timeout_secs: 120,
+        }
+    }
+}
+
+/// One poll's worth of events.
+#[derive(Debug, Clone, Serialize)]
+pub struct EventBatch {
+    pub events: Vec<Event>,
+    /// Cursor for the next poll.
+    pub next_cursor: u64,
+    /// Events between the requested cursor and the first returned event were
+    /// evicted from the buffer; the client should refetch messages.
+    pub missed: bool,
+}
+
+impl From<Replay> for EventBatch {
+    fn from(replay: Replay) -> Self {
+        Self {
+            events: replay.events,
+            next_cursor: replay.next_cursor,
+            missed: replay.missed,
+        }
+    }
+}
+
+/// Polled session: its ring and the runner subscription filling it.
+struct SessionFeed {
+    ring: std::sync::Mutex<EventLog>,
+    notify: Notify,
+    last_poll: std::sync::Mutex<Instant>,
+    ended: std::sync::atomic::AtomicBool,
+}
+
+impl SessionFeed {
+    fn touch(&self) {
+        *self.last_poll.lock().unwrap() = Instant::now();
+    }
+
+    fn is_ended(&self) -> bool {
+        self.ended.load(std::sync::atomic::Ordering::SeqCst)
+    }
+
+    fn end(&self) {
+        self.ended.store(true, std::sync::atomic::Ordering::SeqCst);
+        self.notify.notify_waiters();
+    }
+}
+
+/// Result of a long poll.
+#[derive(Debug, Clone, Serialize)]
+pub struct PollResponse {
+    pub session_id: String,
+    #[serde(flatten)]
+    pub batch: EventBatch,
+    /// The runner subscription ended (session closed); later polls start a
+    /// new feed.
+    pub ended: bool,
+}
+
+/// Per-session event feeds for long-polling clients.
+pub struct SessionEventFeeds {
+    config: LongPollConfig,
+    feeds: std::sync::Mutex<HashMap<String, Arc<SessionFeed>>>,
+}
+
+impl SessionEventFeeds {
+    pub fn new(config: LongPollConfig) -> Self {
+        Self {
+            config,
+            feeds: std::sync::Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn config(&self) -> &LongPollConfig {
+        &self.config
+    }
+
+    /// Clamp a requested wait to the configured bounds.
+    pub fn wait_for(&self, requested: Option<Duration>) -> Duration {
+        requested
+            .unwrap_or(Duration::from_secs(self.config.default_wait_secs))
+            .min(Duration::from_secs(self.config.max_wait_secs))
+    }
+
+    /// Wait up to `wait` for events after `cursor`, subscribing to the
+    /// session on `runner` if no feed is running yet.
+    pub async fn poll(
+        self: &Arc<Self>,
+        runner: &RunnerClient,
+        session_id: &str,
+        cursor: Option<u64>,
+        wait: Duration,
+    ) -> anyhow::Result<PollResponse> {
+        let feed = self.feed(runner, session_id).await?;
+        feed.touch();
+        let deadline = tokio::time::Instant::now() + wait;
+
+        loop {
+            let notified = feed.notify.notified();
+            tokio::pin!(notified);
+            notified.as_mut().enable();
+
+            let batch: EventBatch = feed
+                .ring
+                .lock()
+                .unwrap()
+                .since(cursor, self.config.max_batch)
+                .into();
+            let ended = feed.is_ended();
+            if !batch.events.is_empty() || batch.missed || ended {
+                return Ok(PollResponse {
+                    session_id: session_id.to_string(),
+                    batch,
+                    ended,
+                });
+            }
+            if tokio::time::timeout_at(deadline, notified).await.is_err() {
+                feed.touch();
+                return Ok(PollResponse {
+                    session_id: session_id.to_string(),
+                    batch,
+                    ended: false,
+                });
+            }
+        }
+    }
+
+    async fn feed(
+        self: &Arc<Self>,
+        runner: &RunnerClient,
+        session_id: &str,
+    ) -> anyhow::Result<Arc<SessionFeed>> {
+        if let Some(feed) = self.feeds.lock().unwrap().get(session_id)
+            && !feed.is_ended()
+        {
+            return Ok(Arc::clone(feed));
+        }
+
+        let subscription = runner.agent_subscribe(session_id).await?;
+        let feed = Arc::new(SessionFeed {
+            ring: std::sync::Mutex::new(EventLog::new(self.config.buffer_events)),
+            notify: Notify::new(),
+            last_poll: std::sync::Mutex::new(Instant::now()),
+            ended: std::sync::atomic::AtomicBool::new(false),
+        });
+        {
+            let mut feeds = self.feeds.lock().unwrap();
+            // A concurrent poll may have won the race; keep its feed.
+            if let Some(existing) = feeds.get(session_id)
+                && !existing.is_ended()
+            {
+                return Ok(Arc::clone(existing));
+            }
+            feeds.insert(session_id.to_string(), Arc::clone(&feed));
+        }
+        info!(session_id = %session_id, "Started long-poll event feed");
+
+        let feeds = Arc::clone(self);
+        let session_id = session_id.to_string();
+        let task_feed = Arc::clone(&feed);
+        tokio::spawn(async move {
+            feeds.run_feed(session_id, task_feed, subscription).await;
+        });
+        Ok(feed)
+    }
+
+    async fn run_feed(
+        &self,
+        session_id: String,
+        feed: Arc<SessionFeed>,
+        mut subscription: oqto_runner::client::PiSubscription,
+    ) {
+        let idle_timeout = Duration::from_secs(self.config.idle_timeout_secs.max(1));
+        let mut idle_check = tokio::time::interval(idle_timeout / 4 + Duration::from_secs(1));
+        idle_check.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+
+        loop {
+            tokio::select! {
+                next = subscription.next() => match next {
+                    Some(PiSubscriptionEvent::Event(event)) => {
+                        feed.ring.lock().unwrap().push(*event);
+                        feed.notify.notify_waiters();
+                    }
+                    Some(PiSubscriptionEvent::End { reason }) => {
+                        debug!(session_id = %session_id, "Long-poll feed ended: {}", reason);
+                        break;
+                    }
+                    Some(PiSubscriptionEvent::Error { code, message }) => {
+                        debug!(session_id = %session_id, "Long-poll feed error ({:?}): {}", code, message);
+                        break;
+                    }
+                    // Unparseable or unexpected line; the stream is still open.
+                    None => {}
+                },
+                _ = idle_check.tick() => {
+                    if feed.last_poll.lock().unwrap().elapsed() > idle_timeout {
+                        debug!(session_id = %session_id, "Long-poll feed idle");
+                        break;
+                    }
+                }
+            }
+        }
+
+        feed.end();
+        let mut feeds = self.feeds.lock().unwrap();
+        if feeds
+            .get(&session_id)
+            .is_some_and(|current| Arc::ptr_eq(current, &feed))
+        {
+            feeds.remove(&session_id);
+        }
+        info!(session_id = %session_id, "Stopped long-poll event feed");
+    }
+}
+
+/// Parse a `wait` value: plain seconds (`25`) or with a unit (`25s`,
+/// `1500ms`, `1m`).
+pub fn parse_wait(value: &str) -> Option<Duration> {
+    let value = value.trim();
+    let (number, unit) = match value.find(|c: char| !c.is_ascii_digit()) {
+        Some(idx) => value.split_at(idx),
+        None => (value, "s"),
+    };
+    let number: u64 = number.parse().ok()?;
+    match unit {
+        "ms" => Some(Duration::from_millis(number)),
+        "s" => Some(Duration::from_secs(number)),
+        "m" => Some(Duration::from_secs(number.checked_mul(60)?)),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use oqto_protocol::events::EventPayload;
+
+    fn event(n: i64) -> Event {
+        Event {
+            session_id: "ses_1".to_string(),
+            runner_id: "local".to_string(),
+            ts: n,
+            seq: None,
+            payload: EventPayload::AgentIdle {
+                message_version: None,
+            },
+        }
+    }
+
+    #[test]
+    fn ring_returns_events_after_cursor() {
+        let mut ring = EventLog::new(10);
+        for n in 0..3 {
+            ring.push(event(n));
+        }
+
+        let all = ring.since(None, 100);
+        assert_eq!(all.events.len(), 3);
+        assert_eq!(all.next_cursor, 3);
+        assert!(!all.missed);
+
+        let tail = ring.since(Some(2), 100);
+        assert_eq!(tail.events.len(), 1);
+        assert_eq!(tail.events[0].ts, 2);
+
+        let empty = ring.since(Some(3), 100);
+        assert!(empty.events.is_empty());
+        assert_eq!(empty.next_cursor, 3);
+
+        let limited = ring.since(None, 2);
+        assert_eq!(limited.events.len(), 2);
+        assert_eq!(limited.next_cursor, 2);
+    }
+
+    #[test]
+    fn ring_reports_evicted_events() {
+        let mut ring = EventLog::new(2);
+        for n in 0..5 {
+            ring.push(event(n));
+        }
+
+        let batch = ring.since(Some(1), 100);
+        assert!(batch.missed);
+        assert_eq!(
+            batch.events.iter().map(|e| e.ts).collect::<Vec<_>>(),
+            vec![3, 4]
+        );
+        assert_eq!(batch.next_cursor, 5);
+        assert!(!ring.since(Some(3), 100).missed);
+
+        // An empty poll from before the window still moves the cursor up.
+        let empty = EventLog::new(2).since(Some(7), 100);
+        assert_eq!(empty.next_cursor, 7);
+    }
+
+    #[test]
+    fn parse_wait_units() {
+        assert_eq!(parse_wait("25"), Some(Duration::from_secs(25)));
+        assert_eq!(parse_wait("25s"), Some(Duration::from_secs(25)));
+        assert_eq!(parse_wait("1500ms"), Some(Duration::from_millis(1500)));
+        assert_eq!(parse_wait("1m"), Some(Duration::from_secs(60)));
+        assert_eq!(parse_wait("soon"), None);
+        assert_eq!(parse_wait("5h"), None);
+    }
+}
@@ -0,0 +1,16 @@
+//! Sharing personal sessions with other users.
+//!
+//! The owner of a session can let another user watch it (`read`) or also
+//! prompt and steer it (`write`). A shared session keeps running on its
+//! owner's runner: the WebSocket layer routes participants' agent commands
+//! there, attaches their connections to the same event stream, and tags
+//! their prompts with their name so the agent and the other participants
+//! know who is speaking. Session lifecycle (delete, restart, fork, rename)
+//! stays with the owner. Sessions in shared workspaces are shared through
+//! workspace membership instead.
+
+mod models;
+mod repository;
+
+pub use models::{SessionShare, ShareAccess, ShareSessionRequest};
+pub use repository::SessionShareRepository;
@@ -0,0 +1,56 @@
+use serde::{Deserialize, Serialize};
+
+/// What a participant may do in a shared session.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ShareAccess {
+    /// Watch the session and read its history.
+    Read,
+    /// Also prompt, steer and abort the agent.
+    Write,
+}
+
+impl ShareAccess {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ShareAccess::Read => "read",
+            ShareAccess::Write => "write",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "read" => Some(ShareAccess::Read),
+            "write" => Some(ShareAccess::Write),
+            _ => None,
+        }
+    }
+
+    pub fn can_write(&self) -> bool {
+        *self == ShareAccess::Write
+    }
+}
+
+/// Access of one user to another user's session.
+#[derive(Debug, Clone, Serialize)]
+pub struct SessionShare {
+    pub session_id: String,
+    pub owner_user_id: String,
+    pub user_id: String,
+    pub access: ShareAccess,
+    pub created_at: String,
+    pub updated_at: String,
+}
+
+/// Request body for sharing a session.
+#[derive(Debug, Clone, Deserialize)]
+pub struct ShareSessionRequest {
+    /// User to share with.
+    pub user_id: String,
+    #[serde(default = "default_access")]
+    pub access: ShareAccess,
+}
+
+fn default_access() -> ShareAccess {
+    ShareAccess::Read
+}
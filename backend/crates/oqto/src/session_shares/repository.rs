@@ -0,0 +1,206 @@
+use anyhow::{Context, Result};
+use sqlx::FromRow;
+
+use crate::db::{self, DbPool, on_pool};
+
+use super::{SessionShare, ShareAccess};
+
+const SHARE_COLUMNS: &str = "session_id, owner_user_id, user_id, access, created_at, updated_at";
+
+#[derive(Debug, Clone, FromRow)]
+struct ShareRow {
+    session_id: String,
+    owner_user_id: String,
+    user_id: String,
+    access: String,
+    created_at: String,
+    updated_at: String,
+}
+
+impl From<ShareRow> for SessionShare {
+    fn from(row: ShareRow) -> Self {
+        Self {
+            session_id: row.session_id,
+            owner_user_id: row.owner_user_id,
+            user_id: row.user_id,
+            // The column is constrained to the known values.
+            access: ShareAccess::parse(&row.access).unwrap_or(ShareAccess::Read),
+            created_at: row.created_at,
+            updated_at: row.updated_at,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct SessionShareRepository {
+    pool: DbPool,
+}
+
+impl SessionShareRepository {
+    pub fn new(pool: DbPool) -> Self {
+        Self { pool }
+    }
+
+    /// Share a session with a user, or change their access.
+    pub async fn upsert(
+        &self,
+        session_id: &str,
+        owner_user_id: &str,
+        user_id: &str,
+        access: ShareAccess,
+    ) -> Result<SessionShare> {
+        let now = db::now();
+        on_pool!(&self.pool, |pool| sqlx::query(
+            r#"INSERT INTO session_shares
+                   (session_id, owner_user_id, user_id, access, created_at, updated_at)
+               VALUES ($1, $2, $3, $4, $5, $5)
+               ON CONFLICT (session_id, user_id) DO UPDATE SET
+                   access = excluded.access,
+                   updated_at = excluded.updated_at"#
+        )
+        .bind(session_id)
+        .bind(owner_user_id)
+        .bind(user_id)
+        .bind(access.as_str())
+        .bind(&now)
+        .execute(pool)
+        .await)
+        .context("upsert session share")?;
+
+        self.get(session_id, user_id)
+            .await?
+            .ok_or_else(|| anyhow::anyhow!("Session share not found after upsert"))
+    }
+
+    /// The share of a session with a user, if any.
+    pub async fn get(&self, session_id: &str, user_id: &str) -> Result<Option<SessionShare>> {
+        let sql = format!(
+            "SELECT {SHARE_COLUMNS} FROM session_shares WHERE session_id = $1 AND user_id = $2"
+        );
+        let row = on_pool!(&self.pool, |pool| sqlx::query_as::<_, ShareRow>(&sql)
+            .bind(session_id)
+            .bind(user_id)
+            .fetch_optional(pool)
+            .await)
+        .context("get session share")?;
+        Ok(row.map(Into::into))
+    }
+
+    /// Everyone a session is shared with.
+    pub async fn list_for_session(&self, session_id: &str) -> Result<Vec<SessionShare>> {
+        let sql = format!(
+            "SELECT {SHARE_COLUMNS} FROM session_shares WHERE session_id = $1 ORDER BY created_at, user_id"
+        );
+        let rows = on_pool!(&self.pool, |pool| sqlx::query_as::<_, ShareRow>(&sql)
+            .bind(session_id)
+            .fetch_all(pool)
+            .await)
+        .context("list session shares")?;
+        Ok(rows.into_iter().map(Into::into).collect())
+    }
+
+    /// Sessions other users shared with `user_id`, newest first.
+    pub async fn list_for_user(&self, user_id: &str) -> Result<Vec<SessionShare>> {
+        let sql = format!(
+            "SELECT {SHARE_COLUMNS} FROM session_shares WHERE user_id = $1 ORDER BY created_at DESC, session_id"
+        );
+        let rows = on_pool!(&self.pool, |pool| sqlx::query_as::<_, ShareRow>(&sql)
+            .bind(user_id)
+            .fetch_all(pool)
+            .await)
+        .context("list sessions shared with user")?;
+        Ok(rows.into_iter().map(Into::into).collect())
+    }
+
+    /// Whether a session is shared with anyone.
+    pub async fn is_shared(&self, session_id: &str) -> Result<bool> {
+        let count: i64 = on_pool!(&self.pool, |pool| sqlx::query_scalar(
+            "SELECT COUNT(*) FROM session_shares WHERE session_id = $1"
+        )
+        .bind(session_id)
+        .fetch_one(pool)
+        .await)
+        .context("count session shares")?;
+        Ok(count > 0)
+    }
+
+    pub async fn delete(&self, session_id: &str, user_id: &str) -> Result<bool> {
+        let deleted = on_pool!(&self.pool, |pool| sqlx::query(
+            "DELETE FROM session_shares WHERE session_id = $1 AND user_id = $2"
+        )
+        .bind(session_id)
+        .bind(user_id)
+        .execute(pool)
+        .await
+        .map(|r| r.rows_affected()))
+        .context("delete session share")?;
+        Ok(deleted > 0)
+    }
+
+    /// Stop sharing a session with anyone (when it is deleted).
+    pub async fn delete_for_session(&self, session_id: &str) -> Result<u64> {
+        on_pool!(&self.pool, |pool| sqlx::query(
+            "DELETE FROM session_shares WHERE session_id = $1"
+        )
+        .bind(session_id)
+        .execute(pool)
+        .await
+        .map(|r| r.rows_affected()))
+        .context("delete session shares")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::Database;
+
+    async fn repo() -> SessionShareRepository {
+        let db = Database::in_memory().await.unwrap();
+        for user in ["alice", "bob", "carol"] {
+            sqlx::query(
+                "INSERT INTO users (id, username, email, display_name) VALUES (?, ?, ?, ?)",
+            )
+            .bind(user)
+            .bind(user)
+            .bind(format!("{user}@example.com"))
+            .bind(user)
+            .execute(db.pool())
+            .await
+            .unwrap();
+        }
+        SessionShareRepository::new(db.shared().clone())
+    }
+
+    #[tokio::test]
+    async fn test_share_lifecycle() {
+        let repo = repo().await;
+        assert!(!repo.is_shared("ses_1").await.unwrap());
+
+        let share = repo
+            .upsert("ses_1", "alice", "bob", ShareAccess::Read)
+            .await
+            .unwrap();
+        assert_eq!(share.owner_user_id, "alice");
+        assert_eq!(share.access, ShareAccess::Read);
+        repo.upsert("ses_1", "alice", "carol", ShareAccess::Write)
+            .await
+            .unwrap();
+        assert!(repo.is_shared("ses_1").await.unwrap());
+
+        // Sharing again changes the access.
+        let share = repo
+            .upsert("ses_1", "alice", "bob", ShareAccess::Write)
+            .await
+            .unwrap();
+        assert!(share.access.can_write());
+        assert_eq!(repo.list_for_session("ses_1").await.unwrap().len(), 2);
+        assert_eq!(repo.list_for_user("bob").await.unwrap()[0].session_id, "ses_1");
+        assert!(repo.get("ses_1", "alice").await.unwrap().is_none());
+
+        assert!(repo.delete("ses_1", "bob").await.unwrap());
+        assert!(!repo.delete("ses_1", "bob").await.unwrap());
+        assert_eq!(repo.delete_for_session("ses_1").await.unwrap(), 1);
+        assert!(!repo.is_shared("ses_1").await.unwrap());
+    }
+}
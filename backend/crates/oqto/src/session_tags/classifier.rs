@@ -0,0 +1,327 @@
+//! Prompt classifiers: offline keyword rules, and parsing of model output.
+
+use std::collections::HashMap;
+
+use super::{ClassifiedTag, TOPICS, TagKind};
+
+/// Most topics assigned to one session.
+const MAX_TOPICS: usize = 2;
+/// Most languages or frameworks assigned to one session.
+const MAX_DETECTED: usize = 5;
+/// Longest accepted tag.
+const MAX_TAG_LEN: usize = 32;
+
+/// Words that vote for a topic.
+const TOPIC_KEYWORDS: &[(&str, &[&str])] = &[
+    (
+        "coding",
+        &[
+            "code",
+            "bug",
+            "function",
+            "compile",
+            "compiler",
+            "refactor",
+            "implement",
+            "test",
+            "tests",
+            "stacktrace",
+            "traceback",
+            "struct",
+            "class",
+            "method",
+            "api",
+            "endpoint",
+            "debug",
+            "lint",
+            "regex",
+            "repo",
+            "commit",
+            "merge",
+        ],
+    ),
+    (
+        "research",
+        &[
+            "research",
+            "paper",
+            "papers",
+            "study",
+            "studies",
+            "compare",
+            "comparison",
+            "investigate",
+            "literature",
+            "sources",
+            "survey",
+            "analyze",
+            "analyse",
+            "summarize",
+            "summarise",
+            "explain",
+            "overview",
+        ],
+    ),
+    (
+        "writing",
+        &[
+            "write",
+            "essay",
+            "blog",
+            "draft",
+            "email",
+            "article",
+            "proofread",
+            "rewrite",
+            "letter",
+            "story",
+            "outline",
+            "paragraph",
+            "copy",
+            "tone",
+            "translate",
+            "post",
+        ],
+    ),
+    (
+        "ops",
+        &[
+            "deploy",
+            "deployment",
+            "server",
+            "servers",
+            "docker",
+            "kubernetes",
+            "k8s",
+            "nginx",
+            "systemd",
+            "terraform",
+            "ansible",
+            "helm",
+            "ci",
+            "pipeline",
+            "backup",
+            "monitoring",
+            "prometheus",
+            "grafana",
+            "dns",
+            "ssl",
+            "tls",
+            "firewall",
+            "infrastructure",
+        ],
+    ),
+];
+
+/// Keywords and file extensions per language.
+const LANGUAGES: &[(&str, &[&str], &[&str])] = &[
+    ("rust", &["rust", "cargo", "rustc", "clippy"], &[".rs"]),
+    (
+        "python",
+        &["python", "pip", "pytest", "venv", "pyproject"],
+        &[".py"],
+    ),
+    (
+        "typescript",
+        &["typescript", "tsc", "tsconfig"],
+        &[".ts", ".tsx"],
+    ),
+    (
+        "javascript",
+        &["javascript", "node", "nodejs", "npm"],
+        &[".js", ".jsx", ".mjs"],
+    ),
+    ("go", &["golang", "goroutine", "goroutines"], &[".go"]),
+    ("java", &["java", "maven", "gradle", "jvm"], &[".java"]),
+    ("kotlin", &["kotlin"], &[".kt"]),
+    ("c++", &["c++", "cpp", "cmake"], &[".cpp", ".hpp", ".cc"]),
+    ("c#", &["c#", "csharp", "dotnet"], &[".cs"]),
+    ("ruby", &["ruby", "bundler"], &[".rb"]),
+    ("php", &["php", "composer"], &[".php"]),
+    ("swift", &["swiftui", "xcode"], &[".swift"]),
+    ("shell", &["bash", "zsh", "shell"], &[".sh"]),
+    (
+        "sql",
+        &["sql", "postgres", "postgresql", "sqlite", "mysql"],
+        &[".sql"],
+    ),
+];
+
+/// Keywords per framework.
+const FRAMEWORKS: &[(&str, &[&str])] = &[
+    ("react", &["react", "jsx", "useeffect", "usestate"]),
+    ("nextjs", &["next.js", "nextjs"]),
+    ("vue", &["vue", "vuejs", "nuxt"]),
+    ("svelte", &["svelte", "sveltekit"]),
+    ("angular", &["angular"]),
+    ("tailwind", &["tailwind", "tailwindcss"]),
+    ("django", &["django"]),
+    ("flask", &["flask"]),
+    ("fastapi", &["fastapi"]),
+    ("rails", &["rails"]),
+    ("spring", &["spring", "springboot"]),
+    ("express", &["express", "expressjs"]),
+    ("axum", &["axum"]),
+    ("tokio", &["tokio"]),
+    ("pytorch", &["pytorch", "torch"]),
+    ("pandas", &["pandas"]),
+];
+
+/// Lowercase words of a prompt, keeping the characters of names like
+/// `c++`, `c#`, `next.js` and `main.rs`.
+fn words(prompt: &str) -> Vec<String> {
+    prompt
+        .to_lowercase()
+        .split(|c: char| !(c.is_alphanumeric() || matches!(c, '+' | '#' | '.' | '_' | '-')))
+        .map(|word| word.trim_matches(|c: char| matches!(c, '.' | '-' | '_')))
+        .filter(|word| !word.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+/// Classify a prompt with the keyword rules.
+pub fn classify_with_rules(prompt: &str) -> Vec<ClassifiedTag> {
+    let words = words(prompt);
+    let has_word = |keywords: &[&str]| words.iter().any(|w| keywords.contains(&w.as_str()));
+
+    let mut tags = Vec::new();
+    for (language, keywords, extensions) in LANGUAGES {
+        let has_extension = words.iter().any(|w| {
+            extensions
+                .iter()
+                .any(|ext| w.len() > ext.len() && w.ends_with(ext))
+        });
+        if (has_word(keywords) || has_extension) && tags.len() < MAX_DETECTED {
+            tags.push(ClassifiedTag::new(*language, TagKind::Language));
+        }
+    }
+    let languages = tags.len();
+    for (framework, keywords) in FRAMEWORKS {
+        if has_word(keywords) && tags.len() - languages < MAX_DETECTED {
+            tags.push(ClassifiedTag::new(*framework, TagKind::Framework));
+        }
+    }
+
+    let mut scores: HashMap<&str, usize> = HashMap::new();
+    for (topic, keywords) in TOPIC_KEYWORDS {
+        let score = words
+            .iter()
+            .filter(|w| keywords.contains(&w.as_str()))
+            .count();
+        if score > 0 {
+            scores.insert(*topic, score);
+        }
+    }
+    // Naming a language or framework is a strong hint at coding.
+    if !tags.is_empty() {
+        *scores.entry("coding").or_default() += 2;
+    }
+    let mut topics: Vec<(&str, usize)> = scores.into_iter().collect();
+    topics.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(b.0)));
+    tags.extend(
+        topics
+            .into_iter()
+            .take(MAX_TOPICS)
+            .map(|(topic, _)| ClassifiedTag::new(topic, TagKind::Topic)),
+    );
+    tags
+}
+
+/// Instructions for the classification model.
+pub fn model_instructions() -> String {
+    format!(
+        "Classify the user's request to a coding agent. Reply with JSON only: \
+         {{\"topics\": [...], \"languages\": [...], \"frameworks\": [...]}}. \
+         topics: at most {MAX_TOPICS} of {}. languages: programming languages the request \
+         is about. frameworks: frameworks or libraries it is about. Use short lowercase \
+         names and empty lists when nothing applies.",
+        TOPICS.join(", ")
+    )
+}
+
+fn normalize_tag(tag: &str) -> Option<String> {
+    let tag = tag.trim().to_lowercase().replace(' ', "-");
+    let valid = !tag.is_empty()
+        && tag.len() <= MAX_TAG_LEN
+        && tag
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '+' | '#' | '.' | '-' | '_'));
+    valid.then_some(tag)
+}
+
+/// Parse the model's reply. Unknown topics and malformed names are dropped;
+/// None when the reply holds no JSON object.
+pub fn parse_model_output(text: &str) -> Option<Vec<ClassifiedTag>> {
+    let start = text.find('{')?;
+    let end = text.rfind('}')?;
+    let value: serde_json::Value = serde_json::from_str(text.get(start..=end)?).ok()?;
+    let list = |key: &str| -> Vec<String> {
+        value
+            .get(key)
+            .and_then(|v| v.as_array())
+            .map(|items| {
+                items
+                    .iter()
+                    .filter_map(|item| item.as_str())
+                    .filter_map(normalize_tag)
+                    .collect()
+            })
+            .unwrap_or_default()
+    };
+
+    let mut tags: Vec<ClassifiedTag> = Vec::new();
+    let mut push = |tag: String, kind: TagKind| {
+        if !tags.iter().any(|existing| existing.tag == tag) {
+            tags.push(ClassifiedTag::new(tag, kind));
+        }
+    };
+    for language in list("languages").into_iter().take(MAX_DETECTED) {
+        push(language, TagKind::Language);
+    }
+    for framework in list("frameworks").into_iter().take(MAX_DETECTED) {
+        push(framework, TagKind::Framework);
+    }
+    for topic in list("topics")
+        .into_iter()
+        .filter(|topic| TOPICS.contains(&topic.as_str()))
+        .take(MAX_TOPICS)
+    {
+        push(topic, TagKind::Topic);
+    }
+    Some(tags)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tag_names(tags: &[ClassifiedTag]) -> Vec<&str> {
+        tags.iter().map(|t| t.tag.as_str()).collect()
+    }
+
+    #[test]
+    fn test_rules_detect_languages_frameworks_and_topics() {
+        let tags =
+            classify_with_rules("Fix the failing test in src/main.rs, the axum handler panics");
+        assert_eq!(tag_names(&tags), vec!["rust", "axum", "coding"]);
+
+        let tags = classify_with_rules("Deploy the Next.js app with Docker behind nginx");
+        assert!(tags.contains(&ClassifiedTag::new("nextjs", TagKind::Framework)));
+        assert!(tags.contains(&ClassifiedTag::new("ops", TagKind::Topic)));
+
+        let tags = classify_with_rules("Draft a short blog post about our team offsite");
+        assert_eq!(tag_names(&tags), vec!["writing"]);
+
+        assert!(classify_with_rules("hello there").is_empty());
+    }
+
+    #[test]
+    fn test_parse_model_output() {
+        let tags = parse_model_output(
+            "Sure:\n```json\n{\"topics\": [\"Coding\", \"gardening\"], \"languages\": [\"Python\"], \"frameworks\": [\"FastAPI\", \"not a valid tag!\"]}\n```",
+        )
+        .unwrap();
+        assert_eq!(tag_names(&tags), vec!["python", "fastapi", "coding"]);
+        assert!(parse_model_output("no idea").is_none());
+    }
+}
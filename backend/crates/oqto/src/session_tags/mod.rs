@@ -0,0 +1,136 @@
+//! Automatic session tagging.
+//!
+//! Shortly after a session starts, its first prompt is classified into a
+//! topic ([`TOPICS`]) plus the programming languages and frameworks it is
+//! about. With `[session_tags] model` set the classification is done by that
+//! model through EAVS; otherwise, or when the model fails, by offline keyword
+//! rules. Tags are stored per session, filter chat history
+//! (`GET /api/chat-history?tags=...`) and are rolled up by
+//! `GET /api/analytics/tags`.
+
+mod classifier;
+mod models;
+mod repository;
+
+pub use classifier::{classify_with_rules, parse_model_output};
+pub use models::{
+    ClassifiedTag, SessionTag, SessionTagsConfig, TagKind, TagSource, TagStat, TagStatsQuery,
+};
+pub use repository::SessionTagRepository;
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::{Result, anyhow};
+use dashmap::DashSet;
+use tracing::{debug, warn};
+
+use crate::eavs::{CompletionMessage, CompletionRequest, EavsClient, TrafficLane};
+
+/// Topics a session can be tagged with.
+pub const TOPICS: &[&str] = &["coding", "research", "writing", "ops"];
+
+/// Upper bound on remembered classified sessions before the set is reset.
+const MAX_TRACKED_SESSIONS: usize = 10_000;
+
+/// Session tagging service.
+pub struct SessionTagService {
+    repo: SessionTagRepository,
+    config: SessionTagsConfig,
+    eavs: Option<Arc<EavsClient>>,
+    /// Sessions whose first prompt was already seen.
+    seen: DashSet<String>,
+}
+
+impl SessionTagService {
+    pub fn new(
+        repo: SessionTagRepository,
+        config: SessionTagsConfig,
+        eavs: Option<Arc<EavsClient>>,
+    ) -> Self {
+        Self {
+            repo,
+            config,
+            eavs,
+            seen: DashSet::new(),
+        }
+    }
+
+    pub fn repository(&self) -> &SessionTagRepository {
+        &self.repo
+    }
+
+    /// Observe a prompt sent to a session. The first prompt of an untagged
+    /// session is classified on a background task.
+    pub fn observe_prompt(self: &Arc<Self>, user_id: &str, session_id: &str, prompt: &str) {
+        if self.seen.len() >= MAX_TRACKED_SESSIONS {
+            self.seen.clear();
+        }
+        if !self.seen.insert(session_id.to_string()) {
+            return;
+        }
+
+        let service = Arc::clone(self);
+        let user_id = user_id.to_string();
+        let session_id = session_id.to_string();
+        let prompt: String = prompt.chars().take(self.config.max_prompt_chars).collect();
+        tokio::spawn(async move {
+            if let Err(e) = service.tag_session(&user_id, &session_id, &prompt).await {
+                warn!(session_id = %session_id, "Failed to tag session: {e:#}");
+            }
+        });
+    }
+
+    async fn tag_session(&self, user_id: &str, session_id: &str, prompt: &str) -> Result<()> {
+        if self.repo.has_tags(session_id).await? {
+            return Ok(());
+        }
+        let (tags, source) = self.classify(prompt).await;
+        if tags.is_empty() {
+            return Ok(());
+        }
+        debug!(session_id = %session_id, ?source, count = tags.len(), "Tagged session");
+        self.repo.insert(session_id, user_id, &tags, source).await
+    }
+
+    /// Classify with the model when configured, falling back to the rules.
+    async fn classify(&self, prompt: &str) -> (Vec<ClassifiedTag>, TagSource) {
+        if let Some(eavs) = &self.eavs
+            && !self.config.model.is_empty()
+        {
+            match self.classify_with_model(eavs, prompt).await {
+                Ok(tags) => return (tags, TagSource::Model),
+                Err(e) => debug!("Model classification failed, using rules: {e:#}"),
+            }
+        }
+        (classify_with_rules(prompt), TagSource::Rules)
+    }
+
+    async fn classify_with_model(
+        &self,
+        eavs: &EavsClient,
+        prompt: &str,
+    ) -> Result<Vec<ClassifiedTag>> {
+        let (provider, model) = self
+            .config
+            .model
+            .split_once('/')
+            .ok_or_else(|| anyhow!("session_tags.model must be 'provider/model'"))?;
+        let request = CompletionRequest {
+            model: model.to_string(),
+            messages: vec![
+                CompletionMessage::system(classifier::model_instructions()),
+                CompletionMessage::user(prompt),
+            ],
+            max_tokens: Some(200),
+            temperature: Some(0.0),
+        };
+        let reply = tokio::time::timeout(
+            Duration::from_secs(self.config.model_timeout_secs),
+            eavs.complete(provider, &request, TrafficLane::Background),
+        )
+        .await
+        .map_err(|_| anyhow!("timed out"))??;
+        parse_model_output(&reply).ok_or_else(|| anyhow!("model reply is not JSON"))
+    }
+}
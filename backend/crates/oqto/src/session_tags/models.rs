@@ -0,0 +1,98 @@
+use serde::{Deserialize, Serialize};
+
+/// Automatic session tagging configuration.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SessionTagsConfig {
+    /// Classify the first prompt of each session.
+    pub enabled: bool,
+    /// Model that classifies prompts, as `provider/model` routed through
+    /// EAVS (e.g. `openai/gpt-4o-mini`). Empty uses the keyword rules only.
+    pub model: String,
+    /// Characters of the prompt sent to the model.
+    pub max_prompt_chars: usize,
+    /// Seconds to wait for the model before falling back to the rules.
+    pub model_timeout_secs: u64,
+}
+
+impl Default for SessionTagsConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            model: String::new(),
+            max_prompt_chars: 2000,
+            model_timeout_secs: 10,
+        }
+    }
+}
+
+/// What a tag describes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize, sqlx::Type)]
+#[serde(rename_all = "snake_case")]
+#[sqlx(rename_all = "snake_case")]
+pub enum TagKind {
+    /// One of [`super::TOPICS`].
+    Topic,
+    /// Programming language (e.g. `rust`).
+    Language,
+    /// Framework or library (e.g. `react`).
+    Framework,
+}
+
+/// Which classifier assigned a tag.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, sqlx::Type)]
+#[serde(rename_all = "snake_case")]
+#[sqlx(rename_all = "snake_case")]
+pub enum TagSource {
+    /// The configured model, via EAVS.
+    Model,
+    /// The offline keyword rules.
+    Rules,
+}
+
+/// A tag produced by a classifier.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct ClassifiedTag {
+    pub tag: String,
+    pub kind: TagKind,
+}
+
+impl ClassifiedTag {
+    pub fn new(tag: impl Into<String>, kind: TagKind) -> Self {
+        Self {
+            tag: tag.into(),
+            kind,
+        }
+    }
+}
+
+/// A tag stored on a session.
+#[derive(Debug, Clone, Serialize)]
+pub struct SessionTag {
+    pub session_id: String,
+    pub tag: String,
+    pub kind: TagKind,
+    pub source: TagSource,
+    pub created_at: String,
+}
+
+/// Query parameters for tag rollups.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct TagStatsQuery {
+    pub kind: Option<TagKind>,
+    /// First day to include (YYYY-MM-DD, inclusive).
+    pub since: Option<String>,
+    /// Last day to include (YYYY-MM-DD, inclusive).
+    pub until: Option<String>,
+    /// Restrict to one user (admin endpoint only).
+    pub user_id: Option<String>,
+    pub limit: Option<i64>,
+}
+
+/// Number of sessions carrying a tag.
+#[derive(Debug, Clone, Serialize)]
+pub struct TagStat {
+    pub tag: String,
+    pub kind: TagKind,
+    pub sessions: i64,
+}
@@ -0,0 +1,252 @@
+use std::collections::HashSet;
+
+use anyhow::{Context, Result};
+use sqlx::{FromRow, QueryBuilder, Sqlite, SqlitePool};
+
+use super::{ClassifiedTag, SessionTag, TagKind, TagSource, TagStat, TagStatsQuery};
+
+const TAG_COLUMNS: &str = "session_id, tag, kind, source, created_at";
+const DEFAULT_STATS_LIMIT: i64 = 50;
+const MAX_STATS_LIMIT: i64 = 500;
+
+#[derive(Debug, Clone, FromRow)]
+struct SessionTagRow {
+    session_id: String,
+    tag: String,
+    kind: TagKind,
+    source: TagSource,
+    created_at: String,
+}
+
+impl From<SessionTagRow> for SessionTag {
+    fn from(row: SessionTagRow) -> Self {
+        Self {
+            session_id: row.session_id,
+            tag: row.tag,
+            kind: row.kind,
+            source: row.source,
+            created_at: row.created_at,
+        }
+    }
+}
+
+#[derive(Debug, Clone, FromRow)]
+struct TagStatRow {
+    tag: String,
+    kind: TagKind,
+    sessions: i64,
+}
+
+#[derive(Debug, Clone)]
+pub struct SessionTagRepository {
+    pool: SqlitePool,
+}
+
+impl SessionTagRepository {
+    pub fn new(pool: SqlitePool) -> Self {
+        Self { pool }
+    }
+
+    /// Store classifier output. Tags the session already has are kept.
+    pub async fn insert(
+        &self,
+        session_id: &str,
+        user_id: &str,
+        tags: &[ClassifiedTag],
+        source: TagSource,
+    ) -> Result<()> {
+        let mut tx = self
+            .pool
+            .begin()
+            .await
+            .context("begin session tag transaction")?;
+        for tag in tags {
+            sqlx::query(
+                r#"INSERT OR IGNORE INTO session_tags (session_id, user_id, tag, kind, source)
+                   VALUES (?, ?, ?, ?, ?)"#,
+            )
+            .bind(session_id)
+            .bind(user_id)
+            .bind(&tag.tag)
+            .bind(tag.kind)
+            .bind(source)
+            .execute(&mut *tx)
+            .await
+            .context("insert session tag")?;
+        }
+        tx.commit().await.context("commit session tags")?;
+        Ok(())
+    }
+
+    pub async fn has_tags(&self, session_id: &str) -> Result<bool> {
+        let count: i64 =
+            sqlx::query_scalar("SELECT COUNT(*) FROM session_tags WHERE session_id = ?")
+                .bind(session_id)
+                .fetch_one(&self.pool)
+                .await
+                .context("count session tags")?;
+        Ok(count > 0)
+    }
+
+    /// Tags of every session a user's prompts were classified in.
+    pub async fn for_user(&self, user_id: &str) -> Result<Vec<SessionTag>> {
+        let rows: Vec<SessionTagRow> = sqlx::query_as(&format!(
+            "SELECT {TAG_COLUMNS} FROM session_tags WHERE user_id = ? ORDER BY session_id, kind, tag"
+        ))
+        .bind(user_id)
+        .fetch_all(&self.pool)
+        .await
+        .context("list user session tags")?;
+        Ok(rows.into_iter().map(Into::into).collect())
+    }
+
+    /// Sessions carrying all of `tags`.
+    pub async fn sessions_with_all(&self, tags: &[String]) -> Result<HashSet<String>> {
+        if tags.is_empty() {
+            return Ok(HashSet::new());
+        }
+        let mut qb =
+            QueryBuilder::<Sqlite>::new("SELECT session_id FROM session_tags WHERE tag IN (");
+        let mut separated = qb.separated(", ");
+        for tag in tags {
+            separated.push_bind(tag.clone());
+        }
+        qb.push(") GROUP BY session_id HAVING COUNT(DISTINCT tag) = ")
+            .push_bind(tags.len() as i64);
+
+        let sessions: Vec<String> = qb
+            .build_query_scalar()
+            .fetch_all(&self.pool)
+            .await
+            .context("query sessions by tag")?;
+        Ok(sessions.into_iter().collect())
+    }
+
+    /// Sessions per tag, most common first.
+    ///
+    /// `user_id` restricts results to a single user; `query.user_id` is ignored
+    /// when it is set (callers decide which one applies).
+    pub async fn stats(
+        &self,
+        query: &TagStatsQuery,
+        user_id: Option<&str>,
+    ) -> Result<Vec<TagStat>> {
+        let mut qb = QueryBuilder::<Sqlite>::new(
+            "SELECT tag, kind, COUNT(DISTINCT session_id) AS sessions FROM session_tags WHERE 1 = 1",
+        );
+        if let Some(user_id) = user_id.or(query.user_id.as_deref()) {
+            qb.push(" AND user_id = ").push_bind(user_id.to_string());
+        }
+        if let Some(kind) = query.kind {
+            qb.push(" AND kind = ").push_bind(kind);
+        }
+        if let Some(since) = query.since.as_deref() {
+            qb.push(" AND date(created_at) >= ")
+                .push_bind(since.to_string());
+        }
+        if let Some(until) = query.until.as_deref() {
+            qb.push(" AND date(created_at) <= ")
+                .push_bind(until.to_string());
+        }
+
+        let limit = query
+            .limit
+            .unwrap_or(DEFAULT_STATS_LIMIT)
+            .clamp(1, MAX_STATS_LIMIT);
+        qb.push(" GROUP BY tag, kind ORDER BY sessions DESC, tag LIMIT ")
+            .push_bind(limit);
+
+        let rows = qb
+            .build_query_as::<TagStatRow>()
+            .fetch_all(&self.pool)
+            .await
+            .context("query session tag stats")?;
+        Ok(rows
+            .into_iter()
+            .map(|row| TagStat {
+                tag: row.tag,
+                kind: row.kind,
+                sessions: row.sessions,
+            })
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::Database;
+
+    async fn repo() -> SessionTagRepository {
+        let db = Database::in_memory().await.unwrap();
+        for user in ["alice", "bob"] {
+            sqlx::query(
+                "INSERT INTO users (id, username, email, display_name) VALUES (?, ?, ?, ?)",
+            )
+            .bind(user)
+            .bind(user)
+            .bind(format!("{user}@example.com"))
+            .bind(user)
+            .execute(db.pool())
+            .await
+            .unwrap();
+        }
+        SessionTagRepository::new(db.pool().clone())
+    }
+
+    #[tokio::test]
+    async fn test_tags_filter_and_rollup() {
+        let repo = repo().await;
+        let coding = ClassifiedTag::new("coding", TagKind::Topic);
+        let rust = ClassifiedTag::new("rust", TagKind::Language);
+        repo.insert(
+            "ses_1",
+            "alice",
+            &[coding.clone(), rust.clone()],
+            TagSource::Rules,
+        )
+        .await
+        .unwrap();
+        repo.insert("ses_2", "alice", &[coding.clone()], TagSource::Model)
+            .await
+            .unwrap();
+        repo.insert("ses_3", "bob", &[coding, rust], TagSource::Rules)
+            .await
+            .unwrap();
+
+        assert!(repo.has_tags("ses_1").await.unwrap());
+        assert!(!repo.has_tags("ses_4").await.unwrap());
+        assert_eq!(repo.for_user("alice").await.unwrap().len(), 3);
+
+        let both = repo
+            .sessions_with_all(&["coding".to_string(), "rust".to_string()])
+            .await
+            .unwrap();
+        assert_eq!(
+            both,
+            HashSet::from(["ses_1".to_string(), "ses_3".to_string()])
+        );
+
+        let stats = repo
+            .stats(&TagStatsQuery::default(), Some("alice"))
+            .await
+            .unwrap();
+        assert_eq!(stats[0].tag, "coding");
+        assert_eq!(stats[0].sessions, 2);
+        assert_eq!(stats[1].tag, "rust");
+        assert_eq!(stats[1].sessions, 1);
+
+        let languages = repo
+            .stats(
+                &TagStatsQuery {
+                    kind: Some(TagKind::Language),
+                    ..Default::default()
+                },
+                None,
+            )
+            .await
+            .unwrap();
+        assert_eq!(languages.len(), 1);
+        assert_eq!(languages[0].sessions, 2);
+    }
+}
@@ -0,0 +1,201 @@
+//! Persistent history of settings document patches.
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use sqlx::{FromRow, QueryBuilder, Sqlite, SqlitePool};
+
+use super::patch::PatchOperation;
+
+const DEFAULT_PAGE_LIMIT: i64 = 50;
+const MAX_PAGE_LIMIT: i64 = 500;
+
+/// One applied patch.
+#[derive(Debug, Clone, Serialize, FromRow)]
+pub struct SettingsPatchRecord {
+    pub id: String,
+    pub app: String,
+    pub user_id: String,
+    /// Document version the patch was applied to.
+    pub base_version: String,
+    /// Document version after the patch.
+    pub version: String,
+    /// Operations as applied, with sensitive values redacted.
+    #[sqlx(json)]
+    pub operations: Vec<PatchOperation>,
+    pub created_at: String,
+}
+
+/// Paging for patch history.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct SettingsHistoryQuery {
+    pub limit: Option<i64>,
+    pub offset: Option<i64>,
+}
+
+/// A page of patch history, newest first.
+#[derive(Debug, Clone, Serialize)]
+pub struct SettingsPatchPage {
+    pub patches: Vec<SettingsPatchRecord>,
+    /// Patches matching across all pages.
+    pub total: i64,
+    pub limit: i64,
+    pub offset: i64,
+}
+
+/// A patch to record, keyed by the document's file.
+#[derive(Debug, Clone)]
+pub struct NewSettingsPatch<'a> {
+    pub app: &'a str,
+    pub config_path: &'a str,
+    pub user_id: &'a str,
+    pub base_version: &'a str,
+    pub version: &'a str,
+    pub operations: &'a [PatchOperation],
+}
+
+#[derive(Debug, Clone)]
+pub struct SettingsHistory {
+    pool: SqlitePool,
+}
+
+impl SettingsHistory {
+    pub fn new(pool: SqlitePool) -> Self {
+        Self { pool }
+    }
+
+    pub async fn record(&self, patch: &NewSettingsPatch<'_>) -> Result<()> {
+        sqlx::query(
+            r#"INSERT INTO settings_patches
+                   (id, app, config_path, user_id, base_version, version, operations)
+               VALUES (?, ?, ?, ?, ?, ?, ?)"#,
+        )
+        .bind(format!("sp_{}", uuid::Uuid::new_v4().simple()))
+        .bind(patch.app)
+        .bind(patch.config_path)
+        .bind(patch.user_id)
+        .bind(patch.base_version)
+        .bind(patch.version)
+        .bind(sqlx::types::Json(patch.operations))
+        .execute(&self.pool)
+        .await
+        .context("insert settings patch")?;
+        Ok(())
+    }
+
+    /// A page of the patches to the document at `config_path`, newest first.
+    /// `user_id` restricts the page to one user's patches.
+    pub async fn list(
+        &self,
+        config_path: &str,
+        user_id: Option<&str>,
+        query: &SettingsHistoryQuery,
+    ) -> Result<SettingsPatchPage> {
+        let filter = |qb: &mut QueryBuilder<'_, Sqlite>| {
+            qb.push(" WHERE config_path = ")
+                .push_bind(config_path.to_string());
+            if let Some(user_id) = user_id {
+                qb.push(" AND user_id = ").push_bind(user_id.to_string());
+            }
+        };
+
+        let mut count = QueryBuilder::<Sqlite>::new("SELECT COUNT(*) FROM settings_patches");
+        filter(&mut count);
+        let total: i64 = count
+            .build_query_scalar()
+            .fetch_one(&self.pool)
+            .await
+            .context("count settings patches")?;
+
+        let limit = query
+            .limit
+            .unwrap_or(DEFAULT_PAGE_LIMIT)
+            .clamp(1, MAX_PAGE_LIMIT);
+        let offset = query.offset.unwrap_or(0).max(0);
+        let mut qb = QueryBuilder::<Sqlite>::new(
+            "SELECT id, app, user_id, base_version, version, operations, created_at \
+             FROM settings_patches",
+        );
+        filter(&mut qb);
+        qb.push(" ORDER BY created_at DESC, rowid DESC LIMIT ")
+            .push_bind(limit)
+            .push(" OFFSET ")
+            .push_bind(offset);
+        let patches = qb
+            .build_query_as::<SettingsPatchRecord>()
+            .fetch_all(&self.pool)
+            .await
+            .context("list settings patches")?;
+
+        Ok(SettingsPatchPage {
+            patches,
+            total,
+            limit,
+            offset,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::Database;
+    use serde_json::json;
+
+    #[tokio::test]
+    async fn test_record_and_page_history() {
+        let db = Database::in_memory().await.unwrap();
+        let history = SettingsHistory::new(db.pool().clone());
+        let operations = vec![PatchOperation::Replace {
+            path: "/voice/enabled".to_string(),
+            value: json!(true),
+        }];
+
+        for (user_id, base, version) in [("alice", "v1", "v2"), ("bob", "v2", "v3")] {
+            history
+                .record(&NewSettingsPatch {
+                    app: "oqto",
+                    config_path: "/etc/oqto/config.toml",
+                    user_id,
+                    base_version: base,
+                    version,
+                    operations: &operations,
+                })
+                .await
+                .unwrap();
+        }
+
+        let page = history
+            .list(
+                "/etc/oqto/config.toml",
+                None,
+                &SettingsHistoryQuery::default(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(page.total, 2);
+        assert_eq!(page.patches[0].user_id, "bob");
+        assert_eq!(page.patches[1].operations, operations);
+
+        let own = history
+            .list(
+                "/etc/oqto/config.toml",
+                Some("alice"),
+                &SettingsHistoryQuery {
+                    limit: Some(1),
+                    offset: None,
+                },
+            )
+            .await
+            .unwrap();
+        assert_eq!(own.total, 1);
+        assert_eq!(own.patches[0].version, "v2");
+        assert!(
+            history
+                .list("/other.toml", None, &SettingsHistoryQuery::default())
+                .await
+                .unwrap()
+                .patches
+                .is_empty()
+        );
+    }
+}
@@ -5,9 +5,22 @@
 //! - Config file reading/writing (TOML)
 //! - Value comparison against defaults
 //! - Hot-reload support
+//! - Pushing Pi settings changes into running sessions
+//! - JSON Patch (RFC 6902) edits with schema validation, version checks and
+//!   history
 
+mod history;
+mod patch;
 mod schema;
 mod service;
+mod sync;
+mod validate;
 
+pub use history::{NewSettingsPatch, SettingsHistory, SettingsHistoryQuery, SettingsPatchPage};
+pub use patch::{PatchError, PatchOperation};
 pub use schema::SettingsScope;
-pub use service::{ConfigUpdate, SettingsService, SettingsValue};
+pub use service::{
+    ConfigUpdate, SettingsDocument, SettingsPatch, SettingsPatchError, SettingsService,
+    SettingsValue,
+};
+pub use sync::{SessionSyncStatus, SettingsSync};
@@ -0,0 +1,291 @@
+//! JSON Patch (RFC 6902) over settings documents.
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+/// One JSON Patch operation. Paths are JSON Pointers (RFC 6901).
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "op", rename_all = "lowercase")]
+pub enum PatchOperation {
+    Add { path: String, value: Value },
+    Remove { path: String },
+    Replace { path: String, value: Value },
+    Move { from: String, path: String },
+    Copy { from: String, path: String },
+    Test { path: String, value: Value },
+}
+
+impl PatchOperation {
+    /// The location the operation writes to or tests.
+    pub fn path(&self) -> &str {
+        match self {
+            Self::Add { path, .. }
+            | Self::Remove { path }
+            | Self::Replace { path, .. }
+            | Self::Move { path, .. }
+            | Self::Copy { path, .. }
+            | Self::Test { path, .. } => path,
+        }
+    }
+
+    /// The location a `move` or `copy` reads from.
+    pub fn from(&self) -> Option<&str> {
+        match self {
+            Self::Move { from, .. } | Self::Copy { from, .. } => Some(from),
+            _ => None,
+        }
+    }
+
+    /// Mutable access to the value the operation writes or tests.
+    pub(super) fn value_mut(&mut self) -> Option<&mut Value> {
+        match self {
+            Self::Add { value, .. } | Self::Replace { value, .. } | Self::Test { value, .. } => {
+                Some(value)
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Why a patch could not be applied.
+#[derive(Debug, Clone, PartialEq)]
+pub enum PatchError {
+    /// The patch is malformed or addresses a location that does not exist.
+    Invalid(String),
+    /// A `test` operation did not match.
+    TestFailed(String),
+}
+
+impl std::fmt::Display for PatchError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Invalid(msg) => write!(f, "{msg}"),
+            Self::TestFailed(path) => write!(f, "test failed at '{path}'"),
+        }
+    }
+}
+
+/// Split a JSON Pointer into unescaped reference tokens.
+pub fn parse_pointer(pointer: &str) -> Result<Vec<String>, PatchError> {
+    if pointer.is_empty() {
+        return Ok(Vec::new());
+    }
+    let Some(rest) = pointer.strip_prefix('/') else {
+        return Err(PatchError::Invalid(format!(
+            "invalid JSON pointer '{pointer}': must start with '/'"
+        )));
+    };
+    Ok(rest
+        .split('/')
+        .map(|token| token.replace("~1", "/").replace("~0", "~"))
+        .collect())
+}
+
+/// Apply `operations` in order to a copy of `document`. Either all of them
+/// apply or the document is left untouched.
+pub fn apply_patch(document: &Value, operations: &[PatchOperation]) -> Result<Value, PatchError> {
+    let mut patched = document.clone();
+    for (index, operation) in operations.iter().enumerate() {
+        apply_operation(&mut patched, operation).map_err(|e| match e {
+            PatchError::Invalid(msg) => PatchError::Invalid(format!("operation {index}: {msg}")),
+            failed => failed,
+        })?;
+    }
+    Ok(patched)
+}
+
+fn apply_operation(document: &mut Value, operation: &PatchOperation) -> Result<(), PatchError> {
+    let path = parse_pointer(operation.path())?;
+    match operation {
+        PatchOperation::Add { value, .. } => add(document, &path, value.clone()),
+        PatchOperation::Remove { .. } => remove(document, &path).map(drop),
+        PatchOperation::Replace { value, .. } => {
+            let target = resolve_mut(document, &path)?;
+            *target = value.clone();
+            Ok(())
+        }
+        PatchOperation::Move { from, .. } => {
+            let from = parse_pointer(from)?;
+            if from == path {
+                return Ok(());
+            }
+            if path.starts_with(&from) {
+                return Err(PatchError::Invalid(format!(
+                    "cannot move '{}' into one of its children",
+                    operation.path()
+                )));
+            }
+            let value = remove(document, &from)?;
+            add(document, &path, value)
+        }
+        PatchOperation::Copy { from, .. } => {
+            let value = resolve_mut(document, &parse_pointer(from)?)?.clone();
+            add(document, &path, value)
+        }
+        PatchOperation::Test { value, .. } => {
+            if resolve_mut(document, &path).ok().map(|v| &*v) == Some(value) {
+                Ok(())
+            } else {
+                Err(PatchError::TestFailed(operation.path().to_string()))
+            }
+        }
+    }
+}
+
+fn add(document: &mut Value, path: &[String], value: Value) -> Result<(), PatchError> {
+    let Some((last, parent)) = path.split_last() else {
+        *document = value;
+        return Ok(());
+    };
+    match resolve_mut(document, parent)? {
+        Value::Object(map) => {
+            map.insert(last.clone(), value);
+            Ok(())
+        }
+        Value::Array(items) => {
+            let index = if last == "-" {
+                items.len()
+            } else {
+                array_index(last, items.len() + 1)?
+            };
+            items.insert(index, value);
+            Ok(())
+        }
+        _ => Err(PatchError::Invalid(format!(
+            "cannot add '{last}': parent is not an object or array"
+        ))),
+    }
+}
+
+fn remove(document: &mut Value, path: &[String]) -> Result<Value, PatchError> {
+    let Some((last, parent)) = path.split_last() else {
+        return Err(PatchError::Invalid(
+            "cannot remove the whole document".to_string(),
+        ));
+    };
+    match resolve_mut(document, parent)? {
+        Value::Object(map) => map
+            .remove(last)
+            .ok_or_else(|| PatchError::Invalid(format!("no member '{last}' to remove"))),
+        Value::Array(items) => {
+            let index = array_index(last, items.len())?;
+            Ok(items.remove(index))
+        }
+        _ => Err(PatchError::Invalid(format!(
+            "cannot remove '{last}': parent is not an object or array"
+        ))),
+    }
+}
+
+fn resolve_mut<'a>(document: &'a mut Value, path: &[String]) -> Result<&'a mut Value, PatchError> {
+    let mut current = document;
+    for token in path {
+        current = match current {
+            Value::Object(map) => map
+                .get_mut(token)
+                .ok_or_else(|| PatchError::Invalid(format!("no member '{token}'")))?,
+            Value::Array(items) => {
+                let index = array_index(token, items.len())?;
+                &mut items[index]
+            }
+            _ => {
+                return Err(PatchError::Invalid(format!(
+                    "cannot descend into '{token}': not an object or array"
+                )));
+            }
+        };
+    }
+    Ok(current)
+}
+
+/// Parse an array index token, which must be below `len`.
+fn array_index(token: &str, len: usize) -> Result<usize, PatchError> {
+    let valid = !token.is_empty()
+        && token.bytes().all(|b| b.is_ascii_digit())
+        && (token == "0" || !token.starts_with('0'));
+    match token.parse::<usize>() {
+        Ok(index) if valid && index < len => Ok(index),
+        _ => Err(PatchError::Invalid(format!(
+            "invalid array index '{token}'"
+        ))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn ops(value: Value) -> Vec<PatchOperation> {
+        serde_json::from_value(value).unwrap()
+    }
+
+    #[test]
+    fn test_apply_patch_operations() {
+        let doc = json!({
+            "providers": {
+                "eavs": { "models": [{ "id": "a" }, { "id": "b" }] }
+            },
+            "a/b": 1
+        });
+        let patched = apply_patch(
+            &doc,
+            &ops(json!([
+                { "op": "test", "path": "/a~1b", "value": 1 },
+                { "op": "add", "path": "/providers/eavs/models/1", "value": { "id": "x" } },
+                { "op": "add", "path": "/providers/eavs/models/-", "value": { "id": "z" } },
+                { "op": "remove", "path": "/providers/eavs/models/0" },
+                { "op": "replace", "path": "/providers/eavs/models/1/id", "value": "y" },
+                { "op": "copy", "from": "/providers/eavs", "path": "/providers/copy" },
+                { "op": "move", "from": "/a~1b", "path": "/count" }
+            ])),
+        )
+        .unwrap();
+
+        let models = json!([{ "id": "x" }, { "id": "y" }, { "id": "z" }]);
+        assert_eq!(
+            patched,
+            json!({
+                "providers": {
+                    "eavs": { "models": models },
+                    "copy": { "models": models }
+                },
+                "count": 1
+            })
+        );
+    }
+
+    #[test]
+    fn test_apply_patch_is_atomic() {
+        let doc = json!({ "voice": { "enabled": false } });
+        let err = apply_patch(
+            &doc,
+            &ops(json!([
+                { "op": "replace", "path": "/voice/enabled", "value": true },
+                { "op": "test", "path": "/voice/enabled", "value": false }
+            ])),
+        )
+        .unwrap_err();
+        assert_eq!(err, PatchError::TestFailed("/voice/enabled".to_string()));
+
+        for invalid in [
+            json!([{ "op": "replace", "path": "/missing", "value": 1 }]),
+            json!([{ "op": "remove", "path": "/voice/enabled/x" }]),
+            json!([{ "op": "add", "path": "voice", "value": 1 }]),
+            json!([{ "op": "move", "from": "/voice", "path": "/voice/inner" }]),
+        ] {
+            assert!(matches!(
+                apply_patch(&doc, &ops(invalid)),
+                Err(PatchError::Invalid(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn test_array_index() {
+        assert_eq!(array_index("0", 1), Ok(0));
+        assert!(array_index("01", 3).is_err());
+        assert!(array_index("-1", 3).is_err());
+        assert!(array_index("3", 3).is_err());
+    }
+}
@@ -35,7 +35,7 @@ pub fn filter_schema_by_scope(schema: &Value, viewer_scope: SettingsScope) -> Va
 /// Resolve all $ref references in a JSON schema.
 ///
 /// Handles `allOf` with `$ref` by inlining the referenced definition.
-fn resolve_refs(value: &Value, root: &Value) -> Value {
+pub(super) fn resolve_refs(value: &Value, root: &Value) -> Value {
     match value {
         Value::Object(map) => {
             // Check for allOf with $ref - this is the pattern used in the mmry schema
@@ -119,25 +119,23 @@ fn resolve_ref_path(ref_path: &str, root: &Value) -> Option<Value> {
     Some(current.clone())
 }
 
+/// Scope declared by a property's `x-scope`. Properties without one are
+/// visible to everyone; unknown scopes are treated as admin-only.
+pub(super) fn scope_of(property: &Value) -> SettingsScope {
+    match property.get("x-scope").and_then(Value::as_str) {
+        None | Some("user") => SettingsScope::User,
+        Some(_) => SettingsScope::Admin,
+    }
+}
+
 fn filter_object(value: &Value, viewer_scope: SettingsScope) -> Value {
     match value {
         Value::Object(map) => {
             let mut result = serde_json::Map::new();
 
             for (key, val) in map {
-                // Check if this property has an x-scope
-                if let Some(scope_val) = val.get("x-scope")
-                    && let Some(scope_str) = scope_val.as_str()
-                {
-                    let prop_scope = match scope_str {
-                        "admin" => SettingsScope::Admin,
-                        "user" => SettingsScope::User,
-                        _ => SettingsScope::Admin, // Default to admin for unknown
-                    };
-
-                    if !viewer_scope.can_view(prop_scope) {
-                        continue; // Skip this property
-                    }
+                if !viewer_scope.can_view(scope_of(val)) {
+                    continue; // Skip this property
                 }
 
                 // Recursively filter nested objects
@@ -152,19 +150,8 @@ fn filter_object(value: &Value, viewer_scope: SettingsScope) -> Value {
                 let mut filtered_props = serde_json::Map::new();
 
                 for (key, val) in props_map {
-                    // Check x-scope on each property
-                    if let Some(scope_val) = val.get("x-scope")
-                        && let Some(scope_str) = scope_val.as_str()
-                    {
-                        let prop_scope = match scope_str {
-                            "admin" => SettingsScope::Admin,
-                            "user" => SettingsScope::User,
-                            _ => SettingsScope::Admin,
-                        };
-
-                        if !viewer_scope.can_view(prop_scope) {
-                            continue;
-                        }
+                    if !viewer_scope.can_view(scope_of(&val)) {
+                        continue;
                     }
 
                     filtered_props.insert(key, filter_object(&val, viewer_scope));
@@ -3,12 +3,15 @@
 use anyhow::{Context, Result};
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
+use sha2::{Digest, Sha256};
 use std::collections::HashMap;
 use std::path::{Path, PathBuf};
 use std::sync::Arc;
-use tokio::sync::{RwLock, watch};
+use tokio::sync::{Mutex, RwLock, watch};
 
-use super::schema::{SettingsScope, filter_schema_by_scope};
+use super::patch::{PatchError, PatchOperation, apply_patch, parse_pointer};
+use super::schema::{SettingsScope, filter_schema_by_scope, resolve_refs};
+use super::validate::{hide_scoped, redact, validate, writable};
 
 /// A settings value with metadata about its source.
 #[derive(Debug, Clone, Serialize)]
@@ -29,6 +32,69 @@ pub struct ConfigUpdate {
     pub values: HashMap<String, Value>,
 }
 
+impl ConfigUpdate {
+    /// The update as JSON Patch `add` operations, for the patch history.
+    pub fn operations(&self) -> Vec<PatchOperation> {
+        let mut operations: Vec<PatchOperation> = self
+            .values
+            .iter()
+            .map(|(path, value)| PatchOperation::Add {
+                path: path
+                    .split('.')
+                    .map(|token| format!("/{}", token.replace('~', "~0").replace('/', "~1")))
+                    .collect(),
+                value: value.clone(),
+            })
+            .collect();
+        operations.sort_by(|a, b| a.path().cmp(b.path()));
+        operations
+    }
+}
+
+/// A whole settings document with the version to patch it against.
+#[derive(Debug, Clone, Serialize)]
+pub struct SettingsDocument {
+    /// Content hash of the config file; changes with every write.
+    pub version: String,
+    /// The config file as JSON, without the settings hidden from the caller.
+    pub document: Value,
+}
+
+/// JSON Patch (RFC 6902) request against a settings document.
+#[derive(Debug, Clone, Deserialize)]
+pub struct SettingsPatch {
+    /// Version the patch was made against. When set, the patch is rejected
+    /// if the document changed since.
+    #[serde(default)]
+    pub version: Option<String>,
+    pub operations: Vec<PatchOperation>,
+}
+
+/// A patch that was applied.
+#[derive(Debug, Clone)]
+pub struct AppliedPatch {
+    pub base_version: String,
+    /// The document after the patch, as [`SettingsService::document`] returns it.
+    pub document: SettingsDocument,
+    /// The operations with the values of `x-sensitive` settings redacted.
+    pub redacted: Vec<PatchOperation>,
+}
+
+/// Why a settings patch was rejected.
+#[derive(Debug, thiserror::Error)]
+pub enum SettingsPatchError {
+    #[error("settings changed since version {expected} (now {current})")]
+    Conflict { expected: String, current: String },
+    #[error("cannot modify '{0}': permission denied")]
+    Forbidden(String),
+    #[error("{0}")]
+    Patch(PatchError),
+    #[error("patched settings are invalid: {}", .0.join("; "))]
+    Invalid(Vec<String>),
+    #[error(transparent)]
+    Internal(#[from] anyhow::Error),
+}
+
 /// Settings file format.
 #[derive(Debug, Clone, Copy)]
 enum SettingsFormat {
@@ -50,6 +116,10 @@ pub struct SettingsService {
     values: Arc<RwLock<Value>>,
     /// Reload notification channel
     reload_tx: watch::Sender<()>,
+    /// TOML file layered over the config file (remote config bundle)
+    overlay: Option<PathBuf>,
+    /// Serializes read-modify-write cycles of the config file
+    write_lock: Mutex<()>,
 }
 
 impl SettingsService {
@@ -96,9 +166,37 @@ impl SettingsService {
             format,
             values: Arc::new(RwLock::new(values)),
             reload_tx,
+            overlay: None,
+            write_lock: Mutex::new(()),
         })
     }
 
+    /// Layer a TOML file over the config file, e.g. a cached remote config
+    /// bundle. Overlay values win when reading; updates are still written to
+    /// the config file.
+    pub fn with_overlay(mut self, path: PathBuf) -> Result<Self> {
+        self.overlay = Some(path);
+        self.values = Arc::new(RwLock::new(self.read_values()?));
+        Ok(self)
+    }
+
+    /// Read the config file and overlay from disk.
+    fn read_values(&self) -> Result<Value> {
+        let config_path = self.config_path();
+        let mut values = if config_path.exists() {
+            match self.format {
+                SettingsFormat::Toml => load_toml_as_json(&config_path)?,
+                SettingsFormat::Json => load_json_as_json(&config_path)?,
+            }
+        } else {
+            Value::Object(serde_json::Map::new())
+        };
+        if let Some(overlay) = self.overlay.as_ref().filter(|path| path.exists()) {
+            merge_json(&mut values, load_toml_as_json(overlay)?);
+        }
+        Ok(values)
+    }
+
     /// Get the config file path.
     pub fn config_path(&self) -> PathBuf {
         self.config_dir.join(&self.config_filename)
@@ -157,6 +255,7 @@ impl SettingsService {
             }
         }
 
+        let _guard = self.write_lock.lock().await;
         let config_path = self.config_path();
 
         match self.format {
@@ -212,17 +311,147 @@ impl SettingsService {
         Ok(())
     }
 
-    /// Reload configuration from disk.
-    pub async fn reload(&self) -> Result<()> {
+    /// The config file as a JSON document with its version. Overlay values
+    /// are not included; patches apply to the file alone.
+    pub fn document(&self, scope: SettingsScope) -> Result<SettingsDocument> {
+        let (content, document) = self.read_document()?;
+        Ok(self.scoped_document(&content, document, scope))
+    }
+
+    /// Version of the config file as it is on disk now.
+    pub fn document_version(&self) -> Result<String> {
+        Ok(content_version(&self.read_file()?))
+    }
+
+    /// Apply a JSON Patch to the config file.
+    ///
+    /// The patch must only touch settings visible to `scope`, must leave the
+    /// document valid against the schema (errors the file already had are
+    /// tolerated), and is rejected if `patch.version` is stale.
+    pub async fn patch_document(
+        &self,
+        patch: &SettingsPatch,
+        scope: SettingsScope,
+    ) -> Result<AppliedPatch, SettingsPatchError> {
+        let schema = resolve_refs(&self.schema, &self.schema);
+        for operation in &patch.operations {
+            for pointer in std::iter::once(operation.path()).chain(operation.from()) {
+                let path = parse_pointer(pointer).map_err(SettingsPatchError::Patch)?;
+                if !writable(&schema, &path, scope) {
+                    return Err(SettingsPatchError::Forbidden(pointer.to_string()));
+                }
+            }
+        }
+
+        let _guard = self.write_lock.lock().await;
+        let (content, document) = self.read_document()?;
+        let base_version = content_version(&content);
+        if let Some(expected) = &patch.version
+            && *expected != base_version
+        {
+            return Err(SettingsPatchError::Conflict {
+                expected: expected.clone(),
+                current: base_version,
+            });
+        }
+
+        let patched =
+            apply_patch(&document, &patch.operations).map_err(SettingsPatchError::Patch)?;
+        let existing = validate(&schema, &document);
+        let introduced: Vec<String> = validate(&schema, &patched)
+            .into_iter()
+            .filter(|error| !existing.contains(error))
+            .collect();
+        if !introduced.is_empty() {
+            return Err(SettingsPatchError::Invalid(introduced));
+        }
+
+        let content = match self.format {
+            SettingsFormat::Toml => toml::to_string_pretty(&json_to_toml(&patched)?)
+                .context("Failed to serialize config")?,
+            SettingsFormat::Json => {
+                serde_json::to_string_pretty(&patched).context("Failed to serialize config")?
+            }
+        };
         let config_path = self.config_path();
-        let new_values = if config_path.exists() {
-            match self.format {
-                SettingsFormat::Toml => load_toml_as_json(&config_path)?,
-                SettingsFormat::Json => load_json_as_json(&config_path)?,
+        if let Some(parent) = config_path.parent() {
+            std::fs::create_dir_all(parent).context("Failed to create config directory")?;
+        }
+        std::fs::write(&config_path, &content).context("Failed to write config file")?;
+        self.reload().await?;
+
+        let mut redacted = patch.operations.clone();
+        self.redact_operations(&mut redacted);
+        // Re-read so the document matches what TOML serialization kept.
+        let document = self.document(scope)?;
+        Ok(AppliedPatch {
+            base_version,
+            document,
+            redacted,
+        })
+    }
+
+    /// Replace the values of `x-sensitive` settings in `operations`, e.g.
+    /// before they are stored in the patch history.
+    pub fn redact_operations(&self, operations: &mut [PatchOperation]) {
+        let schema = resolve_refs(&self.schema, &self.schema);
+        for operation in operations {
+            let Ok(path) = parse_pointer(operation.path()) else {
+                continue;
+            };
+            if let Some(value) = operation.value_mut() {
+                redact(&schema, &path, value);
             }
-        } else {
+        }
+    }
+
+    /// Raw config file content; empty if the file does not exist.
+    fn read_file(&self) -> Result<String> {
+        let config_path = self.config_path();
+        if !config_path.exists() {
+            return Ok(String::new());
+        }
+        std::fs::read_to_string(&config_path).context("Failed to read config file")
+    }
+
+    /// Raw config file content and its JSON representation.
+    fn read_document(&self) -> Result<(String, Value)> {
+        let content = self.read_file()?;
+        let document = if content.trim().is_empty() {
             Value::Object(serde_json::Map::new())
+        } else {
+            match self.format {
+                SettingsFormat::Toml => toml_to_json(
+                    &content
+                        .parse::<toml::Value>()
+                        .context("Failed to parse config file")?,
+                )?,
+                SettingsFormat::Json => {
+                    serde_json::from_str(&content).context("Failed to parse config file")?
+                }
+            }
         };
+        Ok((content, document))
+    }
+
+    fn scoped_document(
+        &self,
+        content: &str,
+        mut document: Value,
+        scope: SettingsScope,
+    ) -> SettingsDocument {
+        let schema = resolve_refs(&self.schema, &self.schema);
+        hide_scoped(&schema, &mut document, scope);
+        SettingsDocument {
+            version: content_version(content),
+            document,
+        }
+    }
+
+    /// Reload configuration from disk.
+    pub async fn reload(&self) -> Result<()> {
+        let config_path = self.config_path();
+        let new_values = self.read_values()?;
 
         {
             let mut values = self.values.write().await;
@@ -237,6 +466,11 @@ impl SettingsService {
     }
 }
 
+/// Version of a config file: the first 16 hex digits of its SHA-256.
+fn content_version(content: &str) -> String {
+    hex::encode(Sha256::digest(content.as_bytes()))[..16].to_string()
+}
+
 /// Load a TOML file as JSON Value.
 fn load_toml_as_json(path: &Path) -> Result<Value> {
     let content =
@@ -256,6 +490,23 @@ fn load_json_as_json(path: &Path) -> Result<Value> {
     serde_json::from_str(&content).context("Failed to parse config file")
 }
 
+/// Merge `overlay` into `base`, recursing into objects.
+fn merge_json(base: &mut Value, overlay: Value) {
+    match (base, overlay) {
+        (Value::Object(base), Value::Object(overlay)) => {
+            for (key, value) in overlay {
+                match base.get_mut(&key) {
+                    Some(existing) => merge_json(existing, value),
+                    None => {
+                        base.insert(key, value);
+                    }
+                }
+            }
+        }
+        (base, overlay) => *base = overlay,
+    }
+}
+
 /// Convert TOML Value to JSON Value.
 fn toml_to_json(toml: &toml::Value) -> Result<Value> {
     match toml {
@@ -522,4 +773,113 @@ mod tests {
         assert!(!path_exists_in_schema(&schema, "voice.nonexistent"));
         assert!(!path_exists_in_schema(&schema, "nonexistent"));
     }
+
+    #[tokio::test]
+    async fn test_patch_document() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(
+            dir.path().join("config.toml"),
+            "[voice]\nenabled = false\n\n[server]\nport = 8080\n",
+        )
+        .unwrap();
+        let schema = json!({
+            "type": "object",
+            "properties": {
+                "voice": {
+                    "type": "object",
+                    "x-scope": "user",
+                    "properties": { "enabled": { "type": "boolean" } }
+                },
+                "server": {
+                    "type": "object",
+                    "x-scope": "admin",
+                    "properties": { "port": { "type": "integer" } }
+                }
+            }
+        });
+        let service =
+            SettingsService::new(schema, dir.path().to_path_buf(), "config.toml").unwrap();
+
+        let doc = service.document(SettingsScope::User).unwrap();
+        assert_eq!(doc.document, json!({ "voice": { "enabled": false } }));
+
+        let patch = |version: &str, operations: Value| SettingsPatch {
+            version: Some(version.to_string()),
+            operations: serde_json::from_value(operations).unwrap(),
+        };
+        let applied = service
+            .patch_document(
+                &patch(
+                    &doc.version,
+                    json!([{ "op": "replace", "path": "/voice/enabled", "value": true }]),
+                ),
+                SettingsScope::User,
+            )
+            .await
+            .unwrap();
+        assert_eq!(applied.base_version, doc.version);
+        assert_ne!(applied.document.version, doc.version);
+        let values = service.get_values(SettingsScope::Admin).await;
+        assert_eq!(values["voice.enabled"].value, json!(true));
+        assert_eq!(values["server.port"].value, json!(8080));
+
+        // Stale version.
+        assert!(matches!(
+            service
+                .patch_document(
+                    &patch(
+                        &doc.version,
+                        json!([{ "op": "replace", "path": "/voice/enabled", "value": false }]),
+                    ),
+                    SettingsScope::User,
+                )
+                .await,
+            Err(SettingsPatchError::Conflict { .. })
+        ));
+        let current = applied.document.version;
+        // Admin-only setting.
+        assert!(matches!(
+            service
+                .patch_document(
+                    &patch(
+                        &current,
+                        json!([{ "op": "replace", "path": "/server/port", "value": 1 }]),
+                    ),
+                    SettingsScope::User,
+                )
+                .await,
+            Err(SettingsPatchError::Forbidden(_))
+        ));
+        // Schema violation.
+        assert!(matches!(
+            service
+                .patch_document(
+                    &patch(
+                        &current,
+                        json!([{ "op": "replace", "path": "/server/port", "value": "x" }]),
+                    ),
+                    SettingsScope::Admin,
+                )
+                .await,
+            Err(SettingsPatchError::Invalid(_))
+        ));
+        assert_eq!(service.document_version().unwrap(), current);
+    }
+
+    #[test]
+    fn test_merge_json_overlay_wins() {
+        let mut base = json!({
+            "voice": { "enabled": false, "default_voice": "af_bella" },
+            "sessions": { "max": 2 }
+        });
+        merge_json(&mut base, json!({ "voice": { "enabled": true } }));
+
+        assert_eq!(
+            base,
+            json!({
+                "voice": { "enabled": true, "default_voice": "af_bella" },
+                "sessions": { "max": 2 }
+            })
+        );
+    }
 }
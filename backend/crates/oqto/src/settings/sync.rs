@@ -0,0 +1,527 @@
+//! Pushing Pi settings changes into running sessions.
+//!
+//! Settings saved through the settings API land in the Pi config directory
+//! on the host, which is only read when an agent starts. After a save, the
+//! sync copies `settings.json` and `models.json` into every running
+//! container session of the user (only files whose content differs are
+//! written) and applies the settings Pi can change at runtime
+//! (`defaultThinkingLevel`, `compaction.enabled`, `retry.enabled`) to the
+//! user's agent sessions on the runner. Anything else is reported as
+//! pending until the next session. The outcome is kept per session and
+//! served by `GET /api/settings/sync`.
+
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use anyhow::{Context, Result, bail};
+use async_trait::async_trait;
+use base64::Engine;
+use dashmap::DashMap;
+use oqto_runner::client::RunnerClient;
+use serde::Serialize;
+use serde_json::Value;
+use tokio::sync::Mutex;
+
+use crate::session::{RuntimeMode, Session, SessionService, SessionStatus};
+
+/// Pi config directory inside session containers.
+const CONTAINER_AGENT_DIR: &str = "/home/dev/.pi/agent";
+
+/// Largest file pushed into a container. Content travels as a command
+/// argument, so it has to stay well below the argument size limit.
+const MAX_SYNC_FILE_BYTES: usize = 256 * 1024;
+
+/// Result of pushing one file into a session.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FileSyncStatus {
+    /// The session already had this content.
+    Unchanged,
+    Updated,
+    Failed,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct FileSync {
+    pub file: String,
+    pub status: FileSyncStatus,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// Whether the running agent picked the change up.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ReloadStatus {
+    /// Nothing changed for this session.
+    NotNeeded,
+    /// Runtime settings were applied to the running agent.
+    Applied,
+    /// The agent cannot reload; the change applies to its next session.
+    NextSession,
+    Failed,
+}
+
+/// Where a synced session runs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SyncTarget {
+    /// A container session with its own copy of the Pi config.
+    Container,
+    /// An agent session on the user's runner, which reads the host files.
+    Agent,
+}
+
+/// Outcome of the last sync for one session.
+#[derive(Debug, Clone, Serialize)]
+pub struct SessionSyncStatus {
+    pub session_id: String,
+    pub target: SyncTarget,
+    pub files: Vec<FileSync>,
+    pub reload: ReloadStatus,
+    /// Settings applied to the running agent.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub applied: Vec<String>,
+    /// Changed settings that take effect in the next session.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub pending: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    pub synced_at: String,
+}
+
+/// A Pi setting that can be changed on a running agent.
+#[derive(Debug, Clone, PartialEq)]
+pub enum LiveSetting {
+    ThinkingLevel(String),
+    AutoCompaction(bool),
+    AutoRetry(bool),
+}
+
+impl LiveSetting {
+    pub fn key(&self) -> &'static str {
+        match self {
+            Self::ThinkingLevel(_) => "defaultThinkingLevel",
+            Self::AutoCompaction(_) => "compaction.enabled",
+            Self::AutoRetry(_) => "retry.enabled",
+        }
+    }
+}
+
+/// File access inside one session.
+#[async_trait]
+pub trait SessionFiles: Send + Sync {
+    /// Content of the file, None when it does not exist.
+    async fn read(&self, path: &str) -> Result<Option<Vec<u8>>>;
+    async fn write(&self, path: &str, content: &[u8]) -> Result<()>;
+}
+
+/// [`SessionFiles`] for a container session, through `exec`.
+struct ContainerFiles<'a> {
+    sessions: &'a SessionService,
+    session: &'a Session,
+}
+
+#[async_trait]
+impl SessionFiles for ContainerFiles<'_> {
+    async fn read(&self, path: &str) -> Result<Option<Vec<u8>>> {
+        let script = r#"[ -f "$1" ] && base64 < "$1" || true"#;
+        let output = self
+            .sessions
+            .exec_in_session(self.session, &["sh", "-c", script, "sh", path])
+            .await?;
+        let encoded: String = output.split_whitespace().collect();
+        if encoded.is_empty() {
+            return Ok(None);
+        }
+        let content = base64::engine::general_purpose::STANDARD
+            .decode(encoded)
+            .context("decoding file content")?;
+        Ok(Some(content))
+    }
+
+    async fn write(&self, path: &str, content: &[u8]) -> Result<()> {
+        if content.len() > MAX_SYNC_FILE_BYTES {
+            bail!("file is larger than {MAX_SYNC_FILE_BYTES} bytes");
+        }
+        // Write next to the target and rename, so the agent never reads a
+        // partial file.
+        let script = r#"mkdir -p "$(dirname "$1")" && printf %s "$2" | base64 -d > "$1.oqto-sync" && mv "$1.oqto-sync" "$1""#;
+        let encoded = base64::engine::general_purpose::STANDARD.encode(content);
+        self.sessions
+            .exec_in_session(self.session, &["sh", "-c", script, "sh", path, &encoded])
+            .await?;
+        Ok(())
+    }
+}
+
+/// Pushes Pi settings into running sessions and remembers the outcome.
+pub struct SettingsSync {
+    sessions: Arc<SessionService>,
+    settings_path: PathBuf,
+    models_path: PathBuf,
+    /// settings.json at startup, the starting point of every user.
+    baseline: Value,
+    /// settings.json as of each user's last sync, to tell which settings
+    /// changed.
+    synced: Mutex<HashMap<String, Value>>,
+    /// Outcome of each user's last sync, one entry per session.
+    statuses: DashMap<String, Vec<SessionSyncStatus>>,
+}
+
+impl SettingsSync {
+    /// `settings_path` and `models_path` are the host files the settings
+    /// API writes.
+    pub fn new(
+        sessions: Arc<SessionService>,
+        settings_path: PathBuf,
+        models_path: PathBuf,
+    ) -> Self {
+        let baseline = read_json(&settings_path).unwrap_or(Value::Null);
+        Self {
+            sessions,
+            settings_path,
+            models_path,
+            baseline,
+            synced: Mutex::new(HashMap::new()),
+            statuses: DashMap::new(),
+        }
+    }
+
+    /// Sync the user's running sessions. `runner` is the user's runner,
+    /// whose agent sessions get the runtime settings applied.
+    pub async fn sync_user(
+        &self,
+        user_id: &str,
+        runner: Option<&RunnerClient>,
+    ) -> Result<Vec<SessionSyncStatus>> {
+        let settings = read_json(&self.settings_path).unwrap_or(Value::Null);
+        let (live, pending) = {
+            let mut synced = self.synced.lock().await;
+            let last = synced.get(user_id).unwrap_or(&self.baseline);
+            let changes = diff_settings(last, &settings);
+            synced.insert(user_id.to_string(), settings);
+            changes
+        };
+        let files = self.source_files().await;
+
+        let mut statuses = Vec::new();
+        let sessions = self.sessions.list_sessions_for_user(user_id).await?;
+        for session in sessions.iter().filter(|s| {
+            s.status == SessionStatus::Running && s.runtime_mode == RuntimeMode::Container
+        }) {
+            let target = ContainerFiles {
+                sessions: &self.sessions,
+                session,
+            };
+            let results = sync_files(&target, &files).await;
+            let updated = results.iter().any(|f| f.status == FileSyncStatus::Updated);
+            let failed = results.iter().any(|f| f.status == FileSyncStatus::Failed);
+            statuses.push(SessionSyncStatus {
+                session_id: session.id.clone(),
+                target: SyncTarget::Container,
+                reload: match (failed, updated) {
+                    (true, _) => ReloadStatus::Failed,
+                    (false, true) => ReloadStatus::NextSession,
+                    (false, false) => ReloadStatus::NotNeeded,
+                },
+                pending: results
+                    .iter()
+                    .filter(|f| f.status == FileSyncStatus::Updated)
+                    .map(|f| f.file.clone())
+                    .collect(),
+                files: results,
+                applied: Vec::new(),
+                error: None,
+                synced_at: now(),
+            });
+        }
+
+        if let Some(runner) = runner {
+            statuses.extend(apply_to_agents(runner, &live, &pending).await);
+        }
+
+        tracing::info!(
+            user_id = %user_id,
+            sessions = statuses.len(),
+            "Synced Pi settings to running sessions"
+        );
+        self.statuses.insert(user_id.to_string(), statuses.clone());
+        Ok(statuses)
+    }
+
+    /// Outcome of the user's last sync.
+    pub fn last_sync(&self, user_id: &str) -> Vec<SessionSyncStatus> {
+        self.statuses
+            .get(user_id)
+            .map(|s| s.clone())
+            .unwrap_or_default()
+    }
+
+    async fn source_files(&self) -> Vec<(String, Option<Vec<u8>>)> {
+        let mut files = Vec::new();
+        for path in [&self.settings_path, &self.models_path] {
+            let name = path
+                .file_name()
+                .map(|n| n.to_string_lossy().into_owned())
+                .unwrap_or_default();
+            files.push((name, tokio::fs::read(path).await.ok()));
+        }
+        files
+    }
+}
+
+/// Write every file whose content differs from the session's copy. Files
+/// missing on the host are left alone in the session.
+pub async fn sync_files(
+    target: &dyn SessionFiles,
+    files: &[(String, Option<Vec<u8>>)],
+) -> Vec<FileSync> {
+    let mut results = Vec::with_capacity(files.len());
+    for (name, content) in files {
+        let Some(content) = content else {
+            continue;
+        };
+        let path = format!("{CONTAINER_AGENT_DIR}/{name}");
+        let outcome = async {
+            if target.read(&path).await?.as_ref() == Some(content) {
+                return Ok(FileSyncStatus::Unchanged);
+            }
+            target.write(&path, content).await?;
+            Ok::<_, anyhow::Error>(FileSyncStatus::Updated)
+        }
+        .await;
+        results.push(match outcome {
+            Ok(status) => FileSync {
+                file: name.clone(),
+                status,
+                error: None,
+            },
+            Err(e) => FileSync {
+                file: name.clone(),
+                status: FileSyncStatus::Failed,
+                error: Some(format!("{e:#}")),
+            },
+        });
+    }
+    results
+}
+
+/// Apply runtime settings to every agent session on the runner.
+async fn apply_to_agents(
+    runner: &RunnerClient,
+    live: &[LiveSetting],
+    pending: &[String],
+) -> Vec<SessionSyncStatus> {
+    let agents = match runner.agent_list_sessions().await {
+        Ok(agents) => agents,
+        Err(e) => {
+            tracing::warn!("Listing agent sessions for settings sync failed: {e:#}");
+            return Vec::new();
+        }
+    };
+
+    let mut statuses = Vec::with_capacity(agents.len());
+    for agent in agents {
+        let mut applied = Vec::new();
+        let mut error = None;
+        for setting in live {
+            let result = match setting {
+                LiveSetting::ThinkingLevel(level) => runner
+                    .agent_set_thinking_level(&agent.session_id, level)
+                    .await
+                    .map(|_| ()),
+                LiveSetting::AutoCompaction(enabled) => {
+                    runner
+                        .agent_set_auto_compaction(&agent.session_id, *enabled)
+                        .await
+                }
+                LiveSetting::AutoRetry(enabled) => {
+                    runner
+                        .agent_set_auto_retry(&agent.session_id, *enabled)
+                        .await
+                }
+            };
+            match result {
+                Ok(()) => applied.push(setting.key().to_string()),
+                Err(e) => {
+                    error = Some(format!("{}: {e:#}", setting.key()));
+                    break;
+                }
+            }
+        }
+        let reload = if error.is_some() {
+            ReloadStatus::Failed
+        } else if !applied.is_empty() {
+            ReloadStatus::Applied
+        } else if !pending.is_empty() {
+            ReloadStatus::NextSession
+        } else {
+            ReloadStatus::NotNeeded
+        };
+        statuses.push(SessionSyncStatus {
+            session_id: agent.session_id,
+            target: SyncTarget::Agent,
+            files: Vec::new(),
+            reload,
+            applied,
+            pending: pending.to_vec(),
+            error,
+            synced_at: now(),
+        });
+    }
+    statuses
+}
+
+/// Split the changes between two versions of settings.json into runtime
+/// settings and the keys that need a new session.
+pub fn diff_settings(old: &Value, new: &Value) -> (Vec<LiveSetting>, Vec<String>) {
+    let mut live = Vec::new();
+    let mut pending = Vec::new();
+    let empty = serde_json::Map::new();
+    let old_map = old.as_object().unwrap_or(&empty);
+    let new_map = new.as_object().unwrap_or(&empty);
+
+    let mut keys: Vec<&String> = old_map.keys().chain(new_map.keys()).collect();
+    keys.sort();
+    keys.dedup();
+    for key in keys {
+        let (before, after) = (old_map.get(key), new_map.get(key));
+        if before == after {
+            continue;
+        }
+        let (field, setting): (&str, fn(bool) -> LiveSetting) = match key.as_str() {
+            "defaultThinkingLevel" => {
+                match after.and_then(Value::as_str) {
+                    Some(level) => live.push(LiveSetting::ThinkingLevel(level.to_string())),
+                    None => pending.push(key.clone()),
+                }
+                continue;
+            }
+            "compaction" => ("enabled", LiveSetting::AutoCompaction),
+            "retry" => ("enabled", LiveSetting::AutoRetry),
+            _ => {
+                pending.push(key.clone());
+                continue;
+            }
+        };
+
+        let flag = |value: Option<&Value>| value.and_then(|v| v.get(field)).cloned();
+        let (flag_before, flag_after) = (flag(before), flag(after));
+        if flag_before != flag_after {
+            match flag_after.as_ref().and_then(Value::as_bool) {
+                Some(enabled) => live.push(setting(enabled)),
+                None => pending.push(format!("{key}.{field}")),
+            }
+        }
+        let rest = |value: Option<&Value>| {
+            let mut value = value.cloned().unwrap_or(Value::Null);
+            if let Some(map) = value.as_object_mut() {
+                map.remove(field);
+            }
+            value
+        };
+        if rest(before) != rest(after) {
+            pending.push(key.clone());
+        }
+    }
+    (live, pending)
+}
+
+fn read_json(path: &std::path::Path) -> Option<Value> {
+    let content = std::fs::read_to_string(path).ok()?;
+    serde_json::from_str(&content).ok()
+}
+
+fn now() -> String {
+    chrono::Utc::now().to_rfc3339()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use serde_json::json;
+
+    use super::*;
+
+    #[derive(Default)]
+    struct FakeFiles {
+        files: Mutex<HashMap<String, Vec<u8>>>,
+        writes: Mutex<Vec<String>>,
+    }
+
+    #[async_trait]
+    impl SessionFiles for FakeFiles {
+        async fn read(&self, path: &str) -> Result<Option<Vec<u8>>> {
+            Ok(self.files.lock().unwrap().get(path).cloned())
+        }
+
+        async fn write(&self, path: &str, content: &[u8]) -> Result<()> {
+            self.writes.lock().unwrap().push(path.to_string());
+            self.files
+                .lock()
+                .unwrap()
+                .insert(path.to_string(), content.to_vec());
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_sync_files_writes_only_changes() {
+        let target = FakeFiles::default();
+        target
+            .files
+            .lock()
+            .unwrap()
+            .insert(format!("{CONTAINER_AGENT_DIR}/models.json"), b"{}".to_vec());
+        let files = vec![
+            ("settings.json".to_string(), Some(b"{\"a\":1}".to_vec())),
+            ("models.json".to_string(), Some(b"{}".to_vec())),
+        ];
+
+        let results = sync_files(&target, &files).await;
+        assert_eq!(results[0].status, FileSyncStatus::Updated);
+        assert_eq!(results[1].status, FileSyncStatus::Unchanged);
+        assert_eq!(
+            *target.writes.lock().unwrap(),
+            vec![format!("{CONTAINER_AGENT_DIR}/settings.json")]
+        );
+
+        let again = sync_files(&target, &files).await;
+        assert!(again.iter().all(|f| f.status == FileSyncStatus::Unchanged));
+
+        let missing = vec![("settings.json".to_string(), None)];
+        assert!(sync_files(&target, &missing).await.is_empty());
+    }
+
+    #[test]
+    fn test_diff_settings() {
+        let old = json!({
+            "defaultThinkingLevel": "low",
+            "compaction": { "enabled": true, "reserveTokens": 16384 },
+            "retry": { "enabled": true },
+            "theme": "dark",
+        });
+        let new = json!({
+            "defaultThinkingLevel": "high",
+            "compaction": { "enabled": false, "reserveTokens": 8192 },
+            "retry": { "enabled": true },
+            "theme": "light",
+        });
+
+        let (live, pending) = diff_settings(&old, &new);
+        assert_eq!(
+            live,
+            vec![
+                LiveSetting::AutoCompaction(false),
+                LiveSetting::ThinkingLevel("high".to_string()),
+            ]
+        );
+        assert_eq!(pending, vec!["compaction", "theme"]);
+
+        let (live, pending) = diff_settings(&new, &new);
+        assert!(live.is_empty() && pending.is_empty());
+    }
+}
@@ -0,0 +1,403 @@
+//! Validation of settings documents against their JSON Schema.
+//!
+//! Covers the keywords the settings schemas use: `type`, `enum`, `const`,
+//! numeric and length bounds, `pattern`, `required`, `properties`,
+//! `patternProperties`, `additionalProperties` and `items`. Schemas must have
+//! their `$ref`s resolved first.
+
+use regex::Regex;
+use serde_json::Value;
+
+use super::schema::{SettingsScope, scope_of};
+
+/// Placeholder stored in patch history instead of sensitive values.
+pub const REDACTED: &str = "[redacted]";
+
+/// Violations of `schema` in `value`, each prefixed with the JSON Pointer of
+/// the offending location.
+pub fn validate(schema: &Value, value: &Value) -> Vec<String> {
+    let mut errors = Vec::new();
+    check(schema, value, "", &mut errors);
+    errors
+}
+
+fn check(schema: &Value, value: &Value, pointer: &str, errors: &mut Vec<String>) {
+    let at = if pointer.is_empty() { "/" } else { pointer };
+
+    if let Some(expected) = schema.get("type")
+        && !matches_type(expected, value)
+    {
+        errors.push(format!("{at}: expected {}", type_names(expected)));
+        return;
+    }
+    if let Some(allowed) = schema.get("enum").and_then(Value::as_array)
+        && !allowed.contains(value)
+    {
+        errors.push(format!("{at}: not one of the allowed values"));
+    }
+    if let Some(constant) = schema.get("const")
+        && constant != value
+    {
+        errors.push(format!("{at}: must be {constant}"));
+    }
+
+    match value {
+        Value::Number(n) => {
+            let n = n.as_f64().unwrap_or_default();
+            let bound = |key: &str| schema.get(key).and_then(Value::as_f64);
+            if bound("minimum").is_some_and(|min| n < min)
+                || bound("exclusiveMinimum").is_some_and(|min| n <= min)
+            {
+                errors.push(format!("{at}: below the minimum"));
+            }
+            if bound("maximum").is_some_and(|max| n > max)
+                || bound("exclusiveMaximum").is_some_and(|max| n >= max)
+            {
+                errors.push(format!("{at}: above the maximum"));
+            }
+        }
+        Value::String(s) => {
+            let len = s.chars().count() as u64;
+            if schema
+                .get("minLength")
+                .and_then(Value::as_u64)
+                .is_some_and(|min| len < min)
+            {
+                errors.push(format!("{at}: too short"));
+            }
+            if schema
+                .get("maxLength")
+                .and_then(Value::as_u64)
+                .is_some_and(|max| len > max)
+            {
+                errors.push(format!("{at}: too long"));
+            }
+            if let Some(pattern) = schema.get("pattern").and_then(Value::as_str)
+                && Regex::new(pattern).is_ok_and(|re| !re.is_match(s))
+            {
+                errors.push(format!("{at}: does not match '{pattern}'"));
+            }
+        }
+        Value::Array(items) => {
+            let len = items.len() as u64;
+            if schema
+                .get("minItems")
+                .and_then(Value::as_u64)
+                .is_some_and(|min| len < min)
+            {
+                errors.push(format!("{at}: too few items"));
+            }
+            if schema
+                .get("maxItems")
+                .and_then(Value::as_u64)
+                .is_some_and(|max| len > max)
+            {
+                errors.push(format!("{at}: too many items"));
+            }
+            if let Some(item_schema) = schema.get("items").filter(|s| s.is_object()) {
+                for (index, item) in items.iter().enumerate() {
+                    check(item_schema, item, &format!("{pointer}/{index}"), errors);
+                }
+            }
+        }
+        Value::Object(map) => {
+            for key in schema
+                .get("required")
+                .and_then(Value::as_array)
+                .into_iter()
+                .flatten()
+                .filter_map(Value::as_str)
+            {
+                if !map.contains_key(key) {
+                    errors.push(format!("{at}: missing '{key}'"));
+                }
+            }
+            for (key, child) in map {
+                let child_pointer = format!("{pointer}/{}", escape(key));
+                match property_schema(schema, key) {
+                    Some(child_schema) => check(child_schema, child, &child_pointer, errors),
+                    None if schema.get("additionalProperties") == Some(&Value::Bool(false)) => {
+                        errors.push(format!("{child_pointer}: unknown setting"));
+                    }
+                    None => {}
+                }
+            }
+        }
+        _ => {}
+    }
+}
+
+fn matches_type(expected: &Value, value: &Value) -> bool {
+    match expected {
+        Value::String(name) => matches_type_name(name, value),
+        Value::Array(names) => names
+            .iter()
+            .filter_map(Value::as_str)
+            .any(|name| matches_type_name(name, value)),
+        _ => true,
+    }
+}
+
+fn matches_type_name(name: &str, value: &Value) -> bool {
+    match name {
+        "string" => value.is_string(),
+        "boolean" => value.is_boolean(),
+        "object" => value.is_object(),
+        "array" => value.is_array(),
+        "null" => value.is_null(),
+        "number" => value.is_number(),
+        "integer" => {
+            value.is_i64() || value.is_u64() || value.as_f64().is_some_and(|n| n.fract() == 0.0)
+        }
+        _ => true,
+    }
+}
+
+fn type_names(expected: &Value) -> String {
+    match expected {
+        Value::Array(names) => names
+            .iter()
+            .filter_map(Value::as_str)
+            .collect::<Vec<_>>()
+            .join(" or "),
+        other => other.as_str().unwrap_or("another type").to_string(),
+    }
+}
+
+fn escape(token: &str) -> String {
+    token.replace('~', "~0").replace('/', "~1")
+}
+
+/// The schema of member `key` of an object described by `schema`.
+fn property_schema<'a>(schema: &'a Value, key: &str) -> Option<&'a Value> {
+    if let Some(property) = schema.get("properties").and_then(|p| p.get(key)) {
+        return Some(property);
+    }
+    if let Some(patterns) = schema.get("patternProperties").and_then(Value::as_object) {
+        for (pattern, property) in patterns {
+            if Regex::new(pattern).is_ok_and(|re| re.is_match(key)) {
+                return Some(property);
+            }
+        }
+    }
+    schema.get("additionalProperties").filter(|s| s.is_object())
+}
+
+/// The schema of the child `token` of a value described by `schema`.
+fn child_schema<'a>(schema: &'a Value, token: &str) -> Option<&'a Value> {
+    property_schema(schema, token).or_else(|| schema.get("items").filter(|s| s.is_object()))
+}
+
+/// Whether `scope` may write at the location `path` (reference tokens):
+/// nothing on the way there, nor anything below it, is hidden from the scope.
+/// Only admins may replace the whole document.
+pub fn writable(schema: &Value, path: &[String], scope: SettingsScope) -> bool {
+    if scope == SettingsScope::Admin {
+        return true;
+    }
+    if path.is_empty() {
+        return false;
+    }
+    let mut current = schema;
+    for token in path {
+        let Some(child) = child_schema(current, token) else {
+            // Not described by the schema; validation decides.
+            return true;
+        };
+        if !scope.can_view(scope_of(child)) {
+            return false;
+        }
+        current = child;
+    }
+    !hides_descendants(current, scope)
+}
+
+fn hides_descendants(schema: &Value, scope: SettingsScope) -> bool {
+    let children = ["properties", "patternProperties"]
+        .into_iter()
+        .filter_map(|key| schema.get(key).and_then(Value::as_object))
+        .flat_map(|map| map.values())
+        .chain(
+            ["additionalProperties", "items"]
+                .into_iter()
+                .filter_map(|key| schema.get(key).filter(|s| s.is_object())),
+        );
+    for child in children {
+        if !scope.can_view(scope_of(child)) || hides_descendants(child, scope) {
+            return true;
+        }
+    }
+    false
+}
+
+/// Remove the members of `document` the scope may not see.
+pub fn hide_scoped(schema: &Value, document: &mut Value, scope: SettingsScope) {
+    if scope == SettingsScope::Admin {
+        return;
+    }
+    match document {
+        Value::Object(map) => {
+            map.retain(|key, _| {
+                property_schema(schema, key).is_none_or(|child| scope.can_view(scope_of(child)))
+            });
+            for (key, child) in map.iter_mut() {
+                if let Some(child_schema) = property_schema(schema, key) {
+                    hide_scoped(child_schema, child, scope);
+                }
+            }
+        }
+        Value::Array(items) => {
+            if let Some(item_schema) = schema.get("items").filter(|s| s.is_object()) {
+                for item in items {
+                    hide_scoped(item_schema, item, scope);
+                }
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Replace the values of `x-sensitive` settings in `value`, which is written
+/// at `path`, with [`REDACTED`].
+pub fn redact(schema: &Value, path: &[String], value: &mut Value) {
+    let mut current = schema;
+    for token in path {
+        match child_schema(current, token) {
+            Some(child) => current = child,
+            None => return,
+        }
+    }
+    redact_value(current, value);
+}
+
+fn redact_value(schema: &Value, value: &mut Value) {
+    if schema.get("x-sensitive") == Some(&Value::Bool(true)) {
+        *value = Value::String(REDACTED.to_string());
+        return;
+    }
+    match value {
+        Value::Object(map) => {
+            for (key, child) in map.iter_mut() {
+                if let Some(child_schema) = property_schema(schema, key) {
+                    redact_value(child_schema, child);
+                }
+            }
+        }
+        Value::Array(items) => {
+            if let Some(item_schema) = schema.get("items").filter(|s| s.is_object()) {
+                for item in items {
+                    redact_value(item_schema, item);
+                }
+            }
+        }
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn schema() -> Value {
+        json!({
+            "type": "object",
+            "additionalProperties": false,
+            "properties": {
+                "voice": {
+                    "type": "object",
+                    "x-scope": "user",
+                    "properties": {
+                        "speed": { "type": "number", "minimum": 0.5, "maximum": 2 },
+                        "engine": { "type": "string", "enum": ["kokoro", "piper"] }
+                    }
+                },
+                "server": {
+                    "type": "object",
+                    "x-scope": "admin",
+                    "properties": {
+                        "port": { "type": "integer" },
+                        "token": { "type": "string", "x-sensitive": true }
+                    }
+                },
+                "providers": {
+                    "type": "object",
+                    "patternProperties": {
+                        "^(.*)$": {
+                            "type": "object",
+                            "required": ["baseUrl"],
+                            "properties": { "baseUrl": { "type": "string", "minLength": 1 } }
+                        }
+                    }
+                }
+            }
+        })
+    }
+
+    #[test]
+    fn test_validate() {
+        let schema = schema();
+        let valid = json!({
+            "voice": { "speed": 1.5, "engine": "piper" },
+            "server": { "port": 8080 },
+            "providers": { "eavs": { "baseUrl": "http://localhost" } }
+        });
+        assert!(validate(&schema, &valid).is_empty());
+
+        let invalid = json!({
+            "voice": { "speed": 3, "engine": "say" },
+            "server": { "port": 80.5 },
+            "providers": { "a/b": { "baseUrl": "" }, "c": {} },
+            "extra": true
+        });
+        let mut errors = validate(&schema, &invalid);
+        errors.sort();
+        assert_eq!(
+            errors,
+            [
+                "/extra: unknown setting",
+                "/providers/a~1b/baseUrl: too short",
+                "/providers/c: missing 'baseUrl'",
+                "/server/port: expected integer",
+                "/voice/engine: not one of the allowed values",
+                "/voice/speed: above the maximum",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_writable_by_scope() {
+        let schema = schema();
+        let path = |tokens: &[&str]| tokens.iter().map(|t| t.to_string()).collect::<Vec<_>>();
+        assert!(writable(
+            &schema,
+            &path(&["voice", "speed"]),
+            SettingsScope::User
+        ));
+        assert!(writable(&schema, &path(&["voice"]), SettingsScope::User));
+        assert!(!writable(
+            &schema,
+            &path(&["server", "port"]),
+            SettingsScope::User
+        ));
+        assert!(!writable(&schema, &path(&[]), SettingsScope::User));
+        assert!(writable(
+            &schema,
+            &path(&["server", "port"]),
+            SettingsScope::Admin
+        ));
+        assert!(writable(&schema, &path(&[]), SettingsScope::Admin));
+    }
+
+    #[test]
+    fn test_hide_and_redact() {
+        let schema = schema();
+        let mut doc = json!({ "voice": { "speed": 1 }, "server": { "port": 1 } });
+        hide_scoped(&schema, &mut doc, SettingsScope::User);
+        assert_eq!(doc, json!({ "voice": { "speed": 1 } }));
+
+        let mut server = json!({ "port": 1, "token": "secret" });
+        redact(&schema, &["server".to_string()], &mut server);
+        assert_eq!(server, json!({ "port": 1, "token": REDACTED }));
+    }
+}
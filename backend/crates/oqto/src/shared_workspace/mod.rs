@@ -1,7 +1,9 @@
 //! Shared workspace module.
 //!
 //! Provides shared workspace CRUD, membership management, USERS.md generation,
-//! and user-prefixed prompt support for multi-user collaboration.
+//! user-prefixed prompt support for multi-user collaboration, and per-member
+//! capability grants (chat, terminal, files) enforced by the WebSocket router
+//! and the fileserver proxy.
 
 mod models;
 mod repository;
@@ -10,9 +12,10 @@ mod users_md;
 
 pub use models::{
     AddMemberRequest, AdminSharedWorkspaceInfo, ConvertToSharedRequest,
-    CreateSharedWorkspaceRequest, CreateSharedWorkspaceWorkdirRequest, SharedWorkspaceInfo,
-    SharedWorkspaceMemberInfo, TransferOwnershipRequest, UpdateMemberRequest,
-    UpdateSharedWorkspaceRequest,
+    CreateSharedWorkspaceRequest, CreateSharedWorkspaceWorkdirRequest, SharePermission,
+    SharePermissions, SharedWorkspaceInfo, SharedWorkspaceMemberInfo, TransferOwnershipRequest,
+    UpdateMemberPermissionsRequest, UpdateMemberRequest, UpdateSharedWorkspaceRequest,
 };
 pub use repository::SharedWorkspaceRepository;
+pub(crate) use service::sanitize_display_name;
 pub use service::SharedWorkspaceService;
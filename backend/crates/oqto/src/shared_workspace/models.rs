@@ -40,27 +40,41 @@ impl std::str::FromStr for MemberRole {
     }
 }
 
-impl sqlx::Type<sqlx::Sqlite> for MemberRole {
-    fn type_info() -> sqlx::sqlite::SqliteTypeInfo {
-        <String as sqlx::Type<sqlx::Sqlite>>::type_info()
+// Stored as text, so the impls work on every backend that stores strings.
+impl<DB: sqlx::Database> sqlx::Type<DB> for MemberRole
+where
+    String: sqlx::Type<DB>,
+{
+    fn type_info() -> DB::TypeInfo {
+        <String as sqlx::Type<DB>>::type_info()
+    }
+
+    fn compatible(ty: &DB::TypeInfo) -> bool {
+        <String as sqlx::Type<DB>>::compatible(ty)
     }
 }
 
-impl<'q> sqlx::Encode<'q, sqlx::Sqlite> for MemberRole {
+impl<'q, DB: sqlx::Database> sqlx::Encode<'q, DB> for MemberRole
+where
+    String: sqlx::Encode<'q, DB>,
+{
     fn encode_by_ref(
         &self,
-        buf: &mut <sqlx::Sqlite as sqlx::Database>::ArgumentBuffer<'q>,
+        buf: &mut <DB as sqlx::Database>::ArgumentBuffer<'q>,
     ) -> Result<sqlx::encode::IsNull, sqlx::error::BoxDynError> {
         let s = self.to_string();
-        <String as sqlx::Encode<sqlx::Sqlite>>::encode(s, buf)
+        <String as sqlx::Encode<DB>>::encode(s, buf)
     }
 }
 
-impl<'r> sqlx::Decode<'r, sqlx::Sqlite> for MemberRole {
+impl<'r, DB: sqlx::Database> sqlx::Decode<'r, DB> for MemberRole
+where
+    String: sqlx::Decode<'r, DB>,
+{
     fn decode(
-        value: <sqlx::Sqlite as sqlx::Database>::ValueRef<'r>,
+        value: <DB as sqlx::Database>::ValueRef<'r>,
     ) -> Result<Self, sqlx::error::BoxDynError> {
-        let s = <String as sqlx::Decode<sqlx::Sqlite>>::decode(value)?;
+        let s = <String as sqlx::Decode<DB>>::decode(value)?;
         s.parse().map_err(|e: String| e.into())
     }
 }
@@ -101,6 +115,117 @@ impl MemberRole {
     }
 }
 
+/// A capability a member can hold in a shared workspace.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SharePermission {
+    /// Read session transcripts and state.
+    ChatRead,
+    /// Create sessions, prompt, steer and change session settings.
+    ChatWrite,
+    /// Watch terminal output.
+    TerminalView,
+    /// Type into and resize terminals.
+    TerminalControl,
+    /// Browse and read files.
+    FileRead,
+    /// Create, modify, move and delete files.
+    FileWrite,
+}
+
+impl std::fmt::Display for SharePermission {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SharePermission::ChatRead => write!(f, "chat_read"),
+            SharePermission::ChatWrite => write!(f, "chat_write"),
+            SharePermission::TerminalView => write!(f, "terminal_view"),
+            SharePermission::TerminalControl => write!(f, "terminal_control"),
+            SharePermission::FileRead => write!(f, "file_read"),
+            SharePermission::FileWrite => write!(f, "file_write"),
+        }
+    }
+}
+
+/// Capabilities granted to a member of a shared workspace.
+///
+/// Members without an explicit grant get the defaults of their role
+/// (`for_role`); owners always hold every capability.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "../../../../frontend/src/generated/")]
+pub struct SharePermissions {
+    #[serde(default)]
+    pub chat_read: bool,
+    #[serde(default)]
+    pub chat_write: bool,
+    #[serde(default)]
+    pub terminal_view: bool,
+    #[serde(default)]
+    pub terminal_control: bool,
+    #[serde(default)]
+    pub file_read: bool,
+    #[serde(default)]
+    pub file_write: bool,
+}
+
+impl SharePermissions {
+    /// Every capability.
+    pub const ALL: Self = Self {
+        chat_read: true,
+        chat_write: true,
+        terminal_view: true,
+        terminal_control: true,
+        file_read: true,
+        file_write: true,
+    };
+
+    /// Default capabilities of a role: viewers may only read chats, watch
+    /// terminals and read files; everyone else gets full access.
+    pub fn for_role(role: MemberRole) -> Self {
+        match role {
+            MemberRole::Viewer => Self {
+                chat_read: true,
+                terminal_view: true,
+                file_read: true,
+                ..Self::default()
+            },
+            MemberRole::Owner | MemberRole::Admin | MemberRole::Member => Self::ALL,
+        }
+    }
+
+    /// Whether the capability is granted.
+    pub fn allows(&self, permission: SharePermission) -> bool {
+        match permission {
+            SharePermission::ChatRead => self.chat_read,
+            SharePermission::ChatWrite => self.chat_write,
+            SharePermission::TerminalView => self.terminal_view,
+            SharePermission::TerminalControl => self.terminal_control,
+            SharePermission::FileRead => self.file_read,
+            SharePermission::FileWrite => self.file_write,
+        }
+    }
+
+    /// Make every write grant imply its read grant.
+    pub fn normalized(self) -> Self {
+        Self {
+            chat_read: self.chat_read || self.chat_write,
+            terminal_view: self.terminal_view || self.terminal_control,
+            file_read: self.file_read || self.file_write,
+            ..self
+        }
+    }
+
+    /// Effective capabilities of a member from their role and the stored
+    /// grant (JSON). Unreadable grants fall back to the role defaults.
+    pub fn effective(role: MemberRole, stored: Option<&str>) -> Self {
+        if role == MemberRole::Owner {
+            return Self::ALL;
+        }
+        stored
+            .and_then(|json| serde_json::from_str::<Self>(json).ok())
+            .map(Self::normalized)
+            .unwrap_or_else(|| Self::for_role(role))
+    }
+}
+
 /// Available icons for shared workspaces (Lucide icon names).
 pub const WORKSPACE_ICONS: &[&str] = &[
     "users",
@@ -165,6 +290,16 @@ pub struct SharedWorkspaceMember {
     pub role: MemberRole,
     pub added_at: String,
     pub added_by: Option<String>,
+    /// Explicit capability grant (JSON `SharePermissions`); None means the
+    /// role defaults apply.
+    pub permissions: Option<String>,
+}
+
+impl SharedWorkspaceMember {
+    /// Capabilities this member holds.
+    pub fn effective_permissions(&self) -> SharePermissions {
+        SharePermissions::effective(self.role, self.permissions.as_deref())
+    }
 }
 
 /// Public shared workspace info (returned to clients).
@@ -203,6 +338,15 @@ pub struct SharedWorkspaceMemberInfo {
     #[sqlx(try_from = "String")]
     pub role: MemberRole,
     pub added_at: String,
+    /// Stored capability grant (JSON), if any.
+    #[serde(skip)]
+    pub stored_permissions: Option<String>,
+    /// Effective capabilities of this member.
+    #[sqlx(skip)]
+    pub permissions: SharePermissions,
+    /// Whether `permissions` were set explicitly instead of derived from the role.
+    #[sqlx(skip)]
+    pub custom_permissions: bool,
 }
 
 /// Request to create a shared workspace.
@@ -259,6 +403,15 @@ pub struct UpdateMemberRequest {
     pub role: MemberRole,
 }
 
+/// Request to set a member's capabilities.
+#[derive(Debug, Clone, Deserialize, TS)]
+#[ts(export, export_to = "../../../../frontend/src/generated/")]
+pub struct UpdateMemberPermissionsRequest {
+    /// New grant; null resets the member to the defaults of their role.
+    #[serde(default)]
+    pub permissions: Option<SharePermissions>,
+}
+
 /// Request to convert a personal project into a shared workspace.
 #[derive(Debug, Clone, Deserialize, TS)]
 #[ts(export, export_to = "../../../../frontend/src/generated/")]
@@ -316,3 +469,33 @@ pub struct AdminSharedWorkspaceInfo {
     pub updated_at: String,
     pub member_count: i64,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_effective_permissions() {
+        let viewer = SharePermissions::effective(MemberRole::Viewer, None);
+        assert!(viewer.chat_read && viewer.terminal_view && viewer.file_read);
+        assert!(!viewer.chat_write && !viewer.terminal_control && !viewer.file_write);
+        assert_eq!(
+            SharePermissions::effective(MemberRole::Member, None),
+            SharePermissions::ALL
+        );
+
+        // Write grants imply reads; owners cannot be restricted.
+        let stored = r#"{"terminal_control":true,"file_write":false}"#;
+        let custom = SharePermissions::effective(MemberRole::Member, Some(stored));
+        assert!(custom.terminal_view && custom.terminal_control);
+        assert!(!custom.chat_read && !custom.file_read);
+        assert_eq!(
+            SharePermissions::effective(MemberRole::Owner, Some(stored)),
+            SharePermissions::ALL
+        );
+        assert_eq!(
+            SharePermissions::effective(MemberRole::Viewer, Some("garbage")),
+            SharePermissions::for_role(MemberRole::Viewer)
+        );
+    }
+}
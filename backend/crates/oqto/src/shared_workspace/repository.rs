@@ -1,23 +1,23 @@
 //! Shared workspace repository for database operations.
 
 use anyhow::{Context, Result, bail};
-use sqlx::SqlitePool;
 use tracing::{debug, instrument};
 
 use super::models::{
-    AdminSharedWorkspaceInfo, MemberRole, SharedWorkspace, SharedWorkspaceInfo,
+    AdminSharedWorkspaceInfo, MemberRole, SharePermissions, SharedWorkspace, SharedWorkspaceInfo,
     SharedWorkspaceMember, SharedWorkspaceMemberInfo, WORKSPACE_COLORS, WORKSPACE_ICONS,
 };
+use crate::db::{self, DbPool, on_pool};
 
 /// Repository for shared workspace database operations.
 #[derive(Debug, Clone)]
 pub struct SharedWorkspaceRepository {
-    pool: SqlitePool,
+    pool: DbPool,
 }
 
 impl SharedWorkspaceRepository {
     /// Create a new repository.
-    pub fn new(pool: SqlitePool) -> Self {
+    pub fn new(pool: DbPool) -> Self {
         Self { pool }
     }
 
@@ -83,10 +83,10 @@ impl SharedWorkspaceRepository {
         icon: &str,
         color: &str,
     ) -> Result<SharedWorkspace> {
-        let row = sqlx::query_as::<sqlx::Sqlite, SharedWorkspace>(
+        let row = on_pool!(&self.pool, |pool| sqlx::query_as::<_, SharedWorkspace>(
             r#"
             INSERT INTO shared_workspaces (id, name, slug, linux_user, path, owner_id, description, icon, color)
-            VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)
             RETURNING *
             "#,
         )
@@ -99,8 +99,8 @@ impl SharedWorkspaceRepository {
         .bind(description)
         .bind(icon)
         .bind(color)
-        .fetch_one(&self.pool)
-        .await
+        .fetch_one(pool)
+        .await)
         .context("creating shared workspace")?;
 
         debug!("created shared workspace: id={}, slug={}", id, slug);
@@ -109,43 +109,43 @@ impl SharedWorkspaceRepository {
 
     /// Get a shared workspace by ID.
     pub async fn get_by_id(&self, id: &str) -> Result<Option<SharedWorkspace>> {
-        let row = sqlx::query_as::<sqlx::Sqlite, SharedWorkspace>(
-            "SELECT * FROM shared_workspaces WHERE id = ?",
+        let row = on_pool!(&self.pool, |pool| sqlx::query_as::<_, SharedWorkspace>(
+            "SELECT * FROM shared_workspaces WHERE id = $1",
         )
         .bind(id)
-        .fetch_optional(&self.pool)
-        .await
+        .fetch_optional(pool)
+        .await)
         .context("fetching shared workspace by id")?;
         Ok(row)
     }
 
     /// Get a shared workspace by slug.
     pub async fn get_by_slug(&self, slug: &str) -> Result<Option<SharedWorkspace>> {
-        let row = sqlx::query_as::<sqlx::Sqlite, SharedWorkspace>(
-            "SELECT * FROM shared_workspaces WHERE slug = ?",
+        let row = on_pool!(&self.pool, |pool| sqlx::query_as::<_, SharedWorkspace>(
+            "SELECT * FROM shared_workspaces WHERE slug = $1",
         )
         .bind(slug)
-        .fetch_optional(&self.pool)
-        .await
+        .fetch_optional(pool)
+        .await)
         .context("fetching shared workspace by slug")?;
         Ok(row)
     }
 
     /// Get a shared workspace by filesystem path.
     pub async fn get_by_path(&self, path: &str) -> Result<Option<SharedWorkspace>> {
-        let row = sqlx::query_as::<sqlx::Sqlite, SharedWorkspace>(
-            "SELECT * FROM shared_workspaces WHERE path = ?",
+        let row = on_pool!(&self.pool, |pool| sqlx::query_as::<_, SharedWorkspace>(
+            "SELECT * FROM shared_workspaces WHERE path = $1",
         )
         .bind(path)
-        .fetch_optional(&self.pool)
-        .await
+        .fetch_optional(pool)
+        .await)
         .context("fetching shared workspace by path")?;
         Ok(row)
     }
 
     /// List all shared workspaces a user has access to, with their role and member count.
     pub async fn list_for_user(&self, user_id: &str) -> Result<Vec<SharedWorkspaceInfo>> {
-        let rows = sqlx::query_as::<sqlx::Sqlite, SharedWorkspaceInfo>(
+        let rows = on_pool!(&self.pool, |pool| sqlx::query_as::<_, SharedWorkspaceInfo>(
             r#"
             SELECT
                 sw.id,
@@ -162,13 +162,13 @@ impl SharedWorkspaceRepository {
                 swm.role AS my_role,
                 (SELECT COUNT(*) FROM shared_workspace_members WHERE shared_workspace_id = sw.id) AS member_count
             FROM shared_workspaces sw
-            JOIN shared_workspace_members swm ON swm.shared_workspace_id = sw.id AND swm.user_id = ?
+            JOIN shared_workspace_members swm ON swm.shared_workspace_id = sw.id AND swm.user_id = $1
             ORDER BY sw.name
             "#,
         )
         .bind(user_id)
-        .fetch_all(&self.pool)
-        .await
+        .fetch_all(pool)
+        .await)
         .context("listing shared workspaces for user")?;
         Ok(rows)
     }
@@ -182,15 +182,15 @@ impl SharedWorkspaceRepository {
         icon: Option<&str>,
         color: Option<&str>,
     ) -> Result<SharedWorkspace> {
-        let row = sqlx::query_as::<sqlx::Sqlite, SharedWorkspace>(
+        let row = on_pool!(&self.pool, |pool| sqlx::query_as::<_, SharedWorkspace>(
             r#"
             UPDATE shared_workspaces
-            SET name = COALESCE(?2, name),
-                description = COALESCE(?3, description),
-                icon = COALESCE(?4, icon),
-                color = COALESCE(?5, color),
-                updated_at = datetime('now')
-            WHERE id = ?1
+            SET name = COALESCE($2, name),
+                description = COALESCE($3, description),
+                icon = COALESCE($4, icon),
+                color = COALESCE($5, color),
+                updated_at = $6
+            WHERE id = $1
             RETURNING *
             "#,
         )
@@ -199,8 +199,9 @@ impl SharedWorkspaceRepository {
         .bind(description)
         .bind(icon)
         .bind(color)
-        .fetch_one(&self.pool)
-        .await
+        .bind(db::now())
+        .fetch_one(pool)
+        .await)
         .context("updating shared workspace")?;
 
         Ok(row)
@@ -208,13 +209,16 @@ impl SharedWorkspaceRepository {
 
     /// Delete a shared workspace (cascades to members).
     pub async fn delete(&self, id: &str) -> Result<()> {
-        let result = sqlx::query("DELETE FROM shared_workspaces WHERE id = ?")
-            .bind(id)
-            .execute(&self.pool)
-            .await
-            .context("deleting shared workspace")?;
+        let result = on_pool!(&self.pool, |pool| sqlx::query(
+            "DELETE FROM shared_workspaces WHERE id = $1"
+        )
+        .bind(id)
+        .execute(pool)
+        .await
+        .map(|r| r.rows_affected()))
+        .context("deleting shared workspace")?;
 
-        if result.rows_affected() == 0 {
+        if result == 0 {
             bail!("shared workspace not found: {}", id);
         }
         Ok(())
@@ -233,19 +237,21 @@ impl SharedWorkspaceRepository {
         role: MemberRole,
         added_by: Option<&str>,
     ) -> Result<SharedWorkspaceMember> {
-        let row = sqlx::query_as::<sqlx::Sqlite, SharedWorkspaceMember>(
-            r#"
+        let row = on_pool!(&self.pool, |pool| {
+            sqlx::query_as::<_, SharedWorkspaceMember>(
+                r#"
             INSERT INTO shared_workspace_members (shared_workspace_id, user_id, role, added_by)
-            VALUES (?, ?, ?, ?)
+            VALUES ($1, $2, $3, $4)
             RETURNING *
             "#,
-        )
-        .bind(workspace_id)
-        .bind(user_id)
-        .bind(role)
-        .bind(added_by)
-        .fetch_one(&self.pool)
-        .await
+            )
+            .bind(workspace_id)
+            .bind(user_id)
+            .bind(role)
+            .bind(added_by)
+            .fetch_one(pool)
+            .await
+        })
         .context("adding member to shared workspace")?;
 
         debug!(
@@ -261,23 +267,28 @@ impl SharedWorkspaceRepository {
         workspace_id: &str,
         user_id: &str,
     ) -> Result<Option<SharedWorkspaceMember>> {
-        let row = sqlx::query_as::<sqlx::Sqlite, SharedWorkspaceMember>(
-            r#"
+        let row = on_pool!(&self.pool, |pool| {
+            sqlx::query_as::<_, SharedWorkspaceMember>(
+                r#"
             SELECT * FROM shared_workspace_members
-            WHERE shared_workspace_id = ? AND user_id = ?
+            WHERE shared_workspace_id = $1 AND user_id = $2
             "#,
-        )
-        .bind(workspace_id)
-        .bind(user_id)
-        .fetch_optional(&self.pool)
-        .await
+            )
+            .bind(workspace_id)
+            .bind(user_id)
+            .fetch_optional(pool)
+            .await
+        })
         .context("fetching workspace member")?;
         Ok(row)
     }
 
     /// List all members of a shared workspace with user info.
     pub async fn list_members(&self, workspace_id: &str) -> Result<Vec<SharedWorkspaceMemberInfo>> {
-        let rows = sqlx::query_as::<sqlx::Sqlite, SharedWorkspaceMemberInfo>(
+        let rows = on_pool!(&self.pool, |pool| sqlx::query_as::<
+            _,
+            SharedWorkspaceMemberInfo,
+        >(
             r#"
             SELECT
                 swm.user_id,
@@ -285,10 +296,11 @@ impl SharedWorkspaceRepository {
                 u.display_name,
                 u.avatar_url,
                 swm.role,
-                swm.added_at
+                swm.added_at,
+                swm.permissions AS stored_permissions
             FROM shared_workspace_members swm
             JOIN users u ON u.id = swm.user_id
-            WHERE swm.shared_workspace_id = ?
+            WHERE swm.shared_workspace_id = $1
             ORDER BY
                 CASE swm.role
                     WHEN 'owner' THEN 0
@@ -300,10 +312,19 @@ impl SharedWorkspaceRepository {
             "#,
         )
         .bind(workspace_id)
-        .fetch_all(&self.pool)
-        .await
+        .fetch_all(pool)
+        .await)
         .context("listing workspace members")?;
-        Ok(rows)
+        Ok(rows
+            .into_iter()
+            .map(|mut member| {
+                member.permissions =
+                    SharePermissions::effective(member.role, member.stored_permissions.as_deref());
+                member.custom_permissions =
+                    member.role != MemberRole::Owner && member.stored_permissions.is_some();
+                member
+            })
+            .collect())
     }
 
     /// Update a member's role.
@@ -313,21 +334,55 @@ impl SharedWorkspaceRepository {
         user_id: &str,
         role: MemberRole,
     ) -> Result<()> {
-        let result = sqlx::query(
+        // A role change resets any explicit grant to the new role's defaults.
+        let result = on_pool!(&self.pool, |pool| sqlx::query(
             r#"
             UPDATE shared_workspace_members
-            SET role = ?
-            WHERE shared_workspace_id = ? AND user_id = ?
+            SET role = $1, permissions = NULL
+            WHERE shared_workspace_id = $2 AND user_id = $3
             "#,
         )
         .bind(role)
         .bind(workspace_id)
         .bind(user_id)
-        .execute(&self.pool)
+        .execute(pool)
         .await
+        .map(|r| r.rows_affected()))
         .context("updating member role")?;
 
-        if result.rows_affected() == 0 {
+        if result == 0 {
+            bail!("member {} not found in workspace {}", user_id, workspace_id);
+        }
+        Ok(())
+    }
+
+    /// Set a member's capability grant. `None` restores the role defaults.
+    pub async fn update_member_permissions(
+        &self,
+        workspace_id: &str,
+        user_id: &str,
+        permissions: Option<&SharePermissions>,
+    ) -> Result<()> {
+        let permissions = permissions
+            .map(serde_json::to_string)
+            .transpose()
+            .context("serializing member permissions")?;
+        let result = on_pool!(&self.pool, |pool| sqlx::query(
+            r#"
+            UPDATE shared_workspace_members
+            SET permissions = $1
+            WHERE shared_workspace_id = $2 AND user_id = $3
+            "#,
+        )
+        .bind(permissions)
+        .bind(workspace_id)
+        .bind(user_id)
+        .execute(pool)
+        .await
+        .map(|r| r.rows_affected()))
+        .context("updating member permissions")?;
+
+        if result == 0 {
             bail!("member {} not found in workspace {}", user_id, workspace_id);
         }
         Ok(())
@@ -335,19 +390,20 @@ impl SharedWorkspaceRepository {
 
     /// Remove a member from a shared workspace.
     pub async fn remove_member(&self, workspace_id: &str, user_id: &str) -> Result<()> {
-        let result = sqlx::query(
+        let result = on_pool!(&self.pool, |pool| sqlx::query(
             r#"
             DELETE FROM shared_workspace_members
-            WHERE shared_workspace_id = ? AND user_id = ?
+            WHERE shared_workspace_id = $1 AND user_id = $2
             "#,
         )
         .bind(workspace_id)
         .bind(user_id)
-        .execute(&self.pool)
+        .execute(pool)
         .await
+        .map(|r| r.rows_affected()))
         .context("removing member from workspace")?;
 
-        if result.rows_affected() == 0 {
+        if result == 0 {
             bail!("member {} not found in workspace {}", user_id, workspace_id);
         }
         Ok(())
@@ -357,11 +413,12 @@ impl SharedWorkspaceRepository {
     /// Returns the shared workspace if the path matches or is a subdirectory.
     pub async fn find_workspace_for_path(&self, path: &str) -> Result<Option<SharedWorkspace>> {
         // Check if path starts with any shared workspace path
-        let workspaces =
-            sqlx::query_as::<sqlx::Sqlite, SharedWorkspace>("SELECT * FROM shared_workspaces")
-                .fetch_all(&self.pool)
-                .await
-                .context("fetching all shared workspaces")?;
+        let workspaces = on_pool!(&self.pool, |pool| sqlx::query_as::<_, SharedWorkspace>(
+            "SELECT * FROM shared_workspaces"
+        )
+        .fetch_all(pool)
+        .await)
+        .context("fetching all shared workspaces")?;
 
         for ws in workspaces {
             if path == ws.path || path.starts_with(&format!("{}/", ws.path)) {
@@ -379,53 +436,56 @@ impl SharedWorkspaceRepository {
         old_owner_id: &str,
         new_owner_id: &str,
     ) -> Result<()> {
-        let mut tx = self.pool.begin().await.context("starting transaction")?;
-
-        // Verify new owner is a member
-        let member = sqlx::query_as::<sqlx::Sqlite, SharedWorkspaceMember>(
-            "SELECT * FROM shared_workspace_members WHERE shared_workspace_id = ? AND user_id = ?",
-        )
-        .bind(workspace_id)
-        .bind(new_owner_id)
-        .fetch_optional(&mut *tx)
-        .await
-        .context("checking new owner membership")?;
-
-        if member.is_none() {
-            bail!("new owner must be an existing member of the workspace");
-        }
-
-        // Update workspace owner_id
-        sqlx::query(
-            "UPDATE shared_workspaces SET owner_id = ?, updated_at = datetime('now') WHERE id = ?",
-        )
-        .bind(new_owner_id)
-        .bind(workspace_id)
-        .execute(&mut *tx)
-        .await
-        .context("updating workspace owner")?;
+        on_pool!(&self.pool, |pool| {
+            let mut tx = pool.begin().await.context("starting transaction")?;
+
+            // Verify new owner is a member
+            let member = sqlx::query_as::<_, SharedWorkspaceMember>(
+                "SELECT * FROM shared_workspace_members WHERE shared_workspace_id = $1 AND user_id = $2",
+            )
+            .bind(workspace_id)
+            .bind(new_owner_id)
+            .fetch_optional(&mut *tx)
+            .await
+            .context("checking new owner membership")?;
 
-        // Promote new owner
-        sqlx::query(
-            "UPDATE shared_workspace_members SET role = 'owner' WHERE shared_workspace_id = ? AND user_id = ?",
-        )
-        .bind(workspace_id)
-        .bind(new_owner_id)
-        .execute(&mut *tx)
-        .await
-        .context("promoting new owner")?;
+            if member.is_none() {
+                bail!("new owner must be an existing member of the workspace");
+            }
 
-        // Demote old owner to admin
-        sqlx::query(
-            "UPDATE shared_workspace_members SET role = 'admin' WHERE shared_workspace_id = ? AND user_id = ?",
-        )
-        .bind(workspace_id)
-        .bind(old_owner_id)
-        .execute(&mut *tx)
-        .await
-        .context("demoting old owner")?;
+            // Update workspace owner_id
+            sqlx::query(
+                "UPDATE shared_workspaces SET owner_id = $1, updated_at = $2 WHERE id = $3",
+            )
+            .bind(new_owner_id)
+            .bind(db::now())
+            .bind(workspace_id)
+            .execute(&mut *tx)
+            .await
+            .context("updating workspace owner")?;
+
+            // Promote new owner
+            sqlx::query(
+                "UPDATE shared_workspace_members SET role = 'owner' WHERE shared_workspace_id = $1 AND user_id = $2",
+            )
+            .bind(workspace_id)
+            .bind(new_owner_id)
+            .execute(&mut *tx)
+            .await
+            .context("promoting new owner")?;
+
+            // Demote old owner to admin
+            sqlx::query(
+                "UPDATE shared_workspace_members SET role = 'admin' WHERE shared_workspace_id = $1 AND user_id = $2",
+            )
+            .bind(workspace_id)
+            .bind(old_owner_id)
+            .execute(&mut *tx)
+            .await
+            .context("demoting old owner")?;
 
-        tx.commit().await.context("committing ownership transfer")?;
+            tx.commit().await.context("committing ownership transfer")?;
+        });
         Ok(())
     }
 
@@ -435,7 +495,7 @@ impl SharedWorkspaceRepository {
 
     /// List ALL shared workspaces with member counts (admin only).
     pub async fn admin_list_all(&self) -> Result<Vec<AdminSharedWorkspaceInfo>> {
-        let rows = sqlx::query_as::<sqlx::Sqlite, AdminSharedWorkspaceInfo>(
+        let rows = on_pool!(&self.pool, |pool| sqlx::query_as::<_, AdminSharedWorkspaceInfo>(
             r#"
             SELECT
                 sw.*,
@@ -444,8 +504,8 @@ impl SharedWorkspaceRepository {
             ORDER BY sw.created_at DESC
             "#,
         )
-        .fetch_all(&self.pool)
-        .await
+        .fetch_all(pool)
+        .await)
         .context("admin listing all shared workspaces")?;
         Ok(rows)
     }
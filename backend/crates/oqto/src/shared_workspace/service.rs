@@ -17,7 +17,7 @@ use crate::ws::{WsEvent, WsHub};
 /// - Square brackets (could fake system messages)
 /// - Control characters (newlines, tabs, etc.)
 /// - Trims whitespace
-fn sanitize_display_name(name: &str) -> String {
+pub(crate) fn sanitize_display_name(name: &str) -> String {
     name.chars()
         .filter(|c| *c != '[' && *c != ']' && !c.is_control())
         .collect::<String>()
@@ -27,9 +27,9 @@ fn sanitize_display_name(name: &str) -> String {
 
 use super::models::{
     AddMemberRequest, AdminSharedWorkspaceInfo, ConvertToSharedRequest,
-    CreateSharedWorkspaceRequest, CreateSharedWorkspaceWorkdirRequest, MemberRole, SharedWorkspace,
-    SharedWorkspaceInfo, SharedWorkspaceMemberInfo, TransferOwnershipRequest,
-    UpdateSharedWorkspaceRequest, WORKSPACE_ICONS,
+    CreateSharedWorkspaceRequest, CreateSharedWorkspaceWorkdirRequest, MemberRole,
+    SharePermissions, SharedWorkspace, SharedWorkspaceInfo, SharedWorkspaceMemberInfo,
+    TransferOwnershipRequest, UpdateSharedWorkspaceRequest, WORKSPACE_ICONS,
 };
 use super::repository::SharedWorkspaceRepository;
 use super::users_md::{generate_context_json, generate_users_md};
@@ -434,6 +434,65 @@ impl SharedWorkspaceService {
         Ok(())
     }
 
+    /// Set a member's capabilities, or reset them to the role defaults with
+    /// `None`. Write grants imply the matching read grant.
+    pub async fn update_member_permissions(
+        &self,
+        workspace_id: &str,
+        caller_id: &str,
+        target_user_id: &str,
+        permissions: Option<SharePermissions>,
+    ) -> Result<SharePermissions> {
+        let (_, caller_role) = self
+            .get(workspace_id, caller_id)
+            .await?
+            .ok_or_else(|| anyhow::anyhow!("workspace not found or access denied"))?;
+
+        if !caller_role.can_manage_members() {
+            bail!("insufficient permissions to update member permissions");
+        }
+
+        let target = self
+            .repo
+            .get_member(workspace_id, target_user_id)
+            .await?
+            .ok_or_else(|| anyhow::anyhow!("member not found"))?;
+
+        if target.role == MemberRole::Owner {
+            bail!("cannot change the owner's permissions");
+        }
+
+        // Admins cannot restrict other admins (only owner can)
+        if target.role == MemberRole::Admin && caller_role != MemberRole::Owner {
+            bail!("only the owner can change an admin's permissions");
+        }
+
+        let permissions = permissions.map(SharePermissions::normalized);
+        self.repo
+            .update_member_permissions(workspace_id, target_user_id, permissions.as_ref())
+            .await?;
+        let effective = permissions.unwrap_or_else(|| SharePermissions::for_role(target.role));
+
+        info!(
+            workspace_id = %workspace_id,
+            user_id = %target_user_id,
+            custom = permissions.is_some(),
+            "updated shared workspace member permissions"
+        );
+
+        self.broadcast_change(
+            workspace_id,
+            "member_permissions_changed",
+            Some(serde_json::json!({
+                "user_id": target_user_id,
+                "permissions": effective,
+            })),
+        )
+        .await;
+
+        Ok(effective)
+    }
+
     /// Remove a member from a shared workspace.
     pub async fn remove_member(
         &self,
@@ -555,6 +614,37 @@ impl SharedWorkspaceService {
         }
     }
 
+    /// Capabilities of a user at a path. Returns None when the path is not in
+    /// a shared workspace (personal workspaces are not restricted) and
+    /// `Some(SharePermissions::default())` for non-members.
+    pub async fn permissions_for_path(
+        &self,
+        workspace_path: &str,
+        user_id: &str,
+    ) -> Result<Option<SharePermissions>> {
+        match self.repo.find_workspace_for_path(workspace_path).await? {
+            Some(ws) => self
+                .permissions_for_workspace(&ws.id, user_id)
+                .await
+                .map(Some),
+            None => Ok(None),
+        }
+    }
+
+    /// Capabilities of a user in a shared workspace; nothing for non-members.
+    pub async fn permissions_for_workspace(
+        &self,
+        workspace_id: &str,
+        user_id: &str,
+    ) -> Result<SharePermissions> {
+        Ok(self
+            .repo
+            .get_member(workspace_id, user_id)
+            .await?
+            .map(|m| m.effective_permissions())
+            .unwrap_or_default())
+    }
+
     /// Add a workdir to an existing shared workspace by copying a source directory.
     pub async fn add_workdir_from_source(
         &self,
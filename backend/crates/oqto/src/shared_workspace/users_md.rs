@@ -66,7 +66,7 @@ pub fn generate_context_json() -> String {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::shared_workspace::models::MemberRole;
+    use crate::shared_workspace::models::{MemberRole, SharePermissions};
 
     #[test]
     fn test_generate_users_md() {
@@ -78,6 +78,9 @@ mod tests {
                 avatar_url: None,
                 role: MemberRole::Owner,
                 added_at: "2026-01-01".to_string(),
+                stored_permissions: None,
+                permissions: SharePermissions::for_role(MemberRole::Owner),
+                custom_permissions: false,
             },
             SharedWorkspaceMemberInfo {
                 user_id: "u2".to_string(),
@@ -86,6 +89,9 @@ mod tests {
                 avatar_url: None,
                 role: MemberRole::Member,
                 added_at: "2026-01-02".to_string(),
+                stored_permissions: None,
+                permissions: SharePermissions::for_role(MemberRole::Member),
+                custom_permissions: false,
             },
         ];
 
@@ -0,0 +1,182 @@
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+use serde::{Deserialize, Serialize};
+
+/// Export of audit and authentication events to a SIEM.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SiemConfig {
+    /// Stream audit events to the configured collector. Requires
+    /// `logging.audit_enabled`.
+    pub enabled: bool,
+    pub transport: SiemTransportKind,
+    pub syslog: SyslogConfig,
+    pub http: HttpExportConfig,
+    /// Event names to export (e.g. `auth_login_failed`); empty exports all.
+    pub events: Vec<String>,
+    /// Rename event fields: `{ user_id = "user.id" }`. Dots create nested
+    /// objects; an empty name drops the field.
+    pub field_map: HashMap<String, String>,
+    /// Fields added to every event (after mapping), e.g. `{ "service.name" = "oqto" }`.
+    pub static_fields: HashMap<String, serde_json::Value>,
+    /// Events held while the collector is unreachable; the oldest are
+    /// dropped beyond this.
+    pub buffer_size: usize,
+    /// Most events per delivery (one HTTP request or syslog write).
+    pub batch_size: usize,
+    /// Longest an event waits before a partial batch is sent.
+    pub flush_interval_ms: u64,
+    /// First retry delay after a failed delivery; doubles up to
+    /// `max_retry_backoff_ms`.
+    pub retry_backoff_ms: u64,
+    pub max_retry_backoff_ms: u64,
+}
+
+impl Default for SiemConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            transport: SiemTransportKind::Syslog,
+            syslog: SyslogConfig::default(),
+            http: HttpExportConfig::default(),
+            events: Vec::new(),
+            field_map: HashMap::new(),
+            static_fields: HashMap::new(),
+            buffer_size: 10_000,
+            batch_size: 200,
+            flush_interval_ms: 2_000,
+            retry_backoff_ms: 1_000,
+            max_retry_backoff_ms: 60_000,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SiemTransportKind {
+    /// RFC 5424 messages over TCP (optionally TLS), octet-counted framing.
+    Syslog,
+    /// Batches POSTed to an HTTP collector.
+    Http,
+}
+
+impl std::fmt::Display for SiemTransportKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Syslog => write!(f, "syslog"),
+            Self::Http => write!(f, "http"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SyslogConfig {
+    /// Collector `host:port`.
+    pub address: String,
+    pub tls: bool,
+    /// PEM bundle of CAs trusted for the collector (default: public roots).
+    pub ca_file: Option<PathBuf>,
+    /// Name checked against the collector's certificate (default: the host
+    /// part of `address`).
+    pub server_name: Option<String>,
+    /// Syslog facility keyword, e.g. `auth`, `authpriv`, `local0`.
+    pub facility: String,
+    pub app_name: String,
+    /// HOSTNAME field (default: the machine's hostname).
+    pub hostname: Option<String>,
+}
+
+impl Default for SyslogConfig {
+    fn default() -> Self {
+        Self {
+            address: String::new(),
+            tls: true,
+            ca_file: None,
+            server_name: None,
+            facility: "auth".to_string(),
+            app_name: "oqto".to_string(),
+            hostname: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HttpExportConfig {
+    /// Bulk endpoint, e.g. a Splunk HEC or Logstash HTTP input.
+    pub url: String,
+    /// Extra request headers, e.g. `{ Authorization = "Splunk <token>" }`.
+    pub headers: HashMap<String, String>,
+    pub format: HttpBatchFormat,
+    pub timeout_secs: u64,
+}
+
+impl Default for HttpExportConfig {
+    fn default() -> Self {
+        Self {
+            url: String::new(),
+            headers: HashMap::new(),
+            format: HttpBatchFormat::Ndjson,
+            timeout_secs: 10,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum HttpBatchFormat {
+    /// One JSON object per line (`application/x-ndjson`).
+    Ndjson,
+    /// A JSON array of events.
+    JsonArray,
+}
+
+/// Syslog facility code for a keyword.
+pub(crate) fn facility_code(name: &str) -> Option<u8> {
+    let code = match name.trim().to_ascii_lowercase().as_str() {
+        "kern" => 0,
+        "user" => 1,
+        "daemon" => 3,
+        "auth" => 4,
+        "syslog" => 5,
+        "authpriv" => 10,
+        "audit" => 13,
+        local => {
+            let n: u8 = local.strip_prefix("local")?.parse().ok()?;
+            if n > 7 {
+                return None;
+            }
+            16 + n
+        }
+    };
+    Some(code)
+}
+
+impl SiemConfig {
+    /// Check the settings of the selected transport.
+    pub fn validate(&self) -> anyhow::Result<()> {
+        match self.transport {
+            SiemTransportKind::Syslog => {
+                if self.syslog.address.trim().is_empty() {
+                    anyhow::bail!("siem.syslog.address is required");
+                }
+                if facility_code(&self.syslog.facility).is_none() {
+                    anyhow::bail!("unknown syslog facility '{}'", self.syslog.facility);
+                }
+            }
+            SiemTransportKind::Http => {
+                let url = reqwest::Url::parse(&self.http.url)
+                    .map_err(|e| anyhow::anyhow!("invalid siem.http.url: {e}"))?;
+                if !matches!(url.scheme(), "http" | "https") {
+                    anyhow::bail!("siem.http.url must be http or https");
+                }
+            }
+        }
+        if self.buffer_size == 0 || self.batch_size == 0 {
+            anyhow::bail!("siem.buffer_size and siem.batch_size must be positive");
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,98 @@
+//! Field mapping from audit events to the collector's schema.
+
+use std::collections::HashMap;
+
+use serde_json::{Map, Value};
+
+/// Renames, drops and adds event fields.
+#[derive(Debug, Clone, Default)]
+pub struct FieldMapping {
+    renames: HashMap<String, String>,
+    static_fields: Vec<(String, Value)>,
+}
+
+impl FieldMapping {
+    pub fn new(renames: HashMap<String, String>, static_fields: HashMap<String, Value>) -> Self {
+        let mut static_fields: Vec<_> = static_fields.into_iter().collect();
+        static_fields.sort_by(|a, b| a.0.cmp(&b.0));
+        Self {
+            renames,
+            static_fields,
+        }
+    }
+
+    /// Map a flat audit event object.
+    pub fn apply(&self, event: Value) -> Value {
+        let Value::Object(fields) = event else {
+            return event;
+        };
+        let mut mapped = Map::new();
+        for (name, value) in fields {
+            match self.renames.get(&name) {
+                Some(target) if target.is_empty() => {}
+                Some(target) => insert_path(&mut mapped, target, value),
+                None => insert_path(&mut mapped, &name, value),
+            }
+        }
+        for (name, value) in &self.static_fields {
+            insert_path(&mut mapped, name, value.clone());
+        }
+        Value::Object(mapped)
+    }
+}
+
+/// Insert at a dotted path, creating nested objects. A non-object in the
+/// way is replaced.
+fn insert_path(target: &mut Map<String, Value>, path: &str, value: Value) {
+    match path.split_once('.') {
+        Some((head, rest)) if !head.is_empty() && !rest.is_empty() => {
+            let child = target
+                .entry(head.to_string())
+                .or_insert_with(|| Value::Object(Map::new()));
+            if !child.is_object() {
+                *child = Value::Object(Map::new());
+            }
+            if let Value::Object(child) = child {
+                insert_path(child, rest, value);
+            }
+        }
+        _ => {
+            target.insert(path.to_string(), value);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn renames_nest_and_drop_fields() {
+        let mapping = FieldMapping::new(
+            HashMap::from([
+                ("timestamp".to_string(), "@timestamp".to_string()),
+                ("user_id".to_string(), "user.id".to_string()),
+                ("username".to_string(), "user.name".to_string()),
+                ("duration_ms".to_string(), String::new()),
+            ]),
+            HashMap::from([("service.name".to_string(), json!("oqto"))]),
+        );
+        let event = json!({
+            "timestamp": "2026-05-01T12:00:00.000Z",
+            "event": "auth_login_failed",
+            "user_id": "u1",
+            "username": "alice",
+            "duration_ms": 3,
+        });
+        assert_eq!(
+            mapping.apply(event),
+            json!({
+                "@timestamp": "2026-05-01T12:00:00.000Z",
+                "event": "auth_login_failed",
+                "user": {"id": "u1", "name": "alice"},
+                "service": {"name": "oqto"},
+            })
+        );
+    }
+}
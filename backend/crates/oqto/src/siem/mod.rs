@@ -0,0 +1,239 @@
+//! Export of audit and authentication events to an external SIEM.
+//!
+//! Every event written to the audit log is also queued here, mapped to the
+//! collector's field names and delivered in batches over syslog (TCP or
+//! TLS) or HTTP. While the collector is unreachable events stay queued and
+//! delivery is retried with backoff; beyond `buffer_size` the oldest are
+//! dropped and counted. `health()` reports the state of the pipeline.
+
+mod config;
+mod mapping;
+mod transport;
+
+use std::collections::{HashSet, VecDeque};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use tokio::sync::Notify;
+use tracing::{info, warn};
+
+pub use config::{SiemConfig, SiemTransportKind};
+pub(crate) use transport::local_hostname;
+
+use crate::audit::AuditEvent;
+use mapping::FieldMapping;
+use transport::Transport;
+
+/// An event waiting for delivery.
+#[derive(Debug, Clone)]
+pub(crate) struct QueuedEvent {
+    /// Audit event name (`http_request`, `auth_login_failed`, ...).
+    pub name: String,
+    /// RFC 3339 time the event happened.
+    pub timestamp: String,
+    /// The event after field mapping.
+    pub body: serde_json::Value,
+}
+
+/// State of the export pipeline.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ExportHealth {
+    pub enabled: bool,
+    /// Last delivery succeeded (or nothing was sent yet).
+    pub healthy: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub transport: Option<SiemTransportKind>,
+    /// Collector address, or the HTTP URL without query.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub destination: Option<String>,
+    pub queued: usize,
+    pub buffer_size: usize,
+    pub sent_total: u64,
+    /// Events discarded because the buffer was full.
+    pub dropped_total: u64,
+    pub failed_deliveries: u64,
+    pub consecutive_failures: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_success_at: Option<DateTime<Utc>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_error: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_error_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Default)]
+struct Counters {
+    sent_total: u64,
+    dropped_total: u64,
+    failed_deliveries: u64,
+    consecutive_failures: u64,
+    last_success_at: Option<DateTime<Utc>>,
+    last_error: Option<String>,
+    last_error_at: Option<DateTime<Utc>>,
+}
+
+struct Shared {
+    config: SiemConfig,
+    mapping: FieldMapping,
+    events: HashSet<String>,
+    queue: Mutex<VecDeque<QueuedEvent>>,
+    counters: Mutex<Counters>,
+    wake: Notify,
+}
+
+impl Shared {
+    /// Drop the oldest events beyond the buffer size.
+    fn trim(&self, queue: &mut VecDeque<QueuedEvent>) {
+        let excess = queue.len().saturating_sub(self.config.buffer_size);
+        if excess > 0 {
+            queue.drain(..excess);
+            self.counters.lock().unwrap().dropped_total += excess as u64;
+        }
+    }
+}
+
+/// Streams audit events to the configured collector.
+pub struct SiemExporter {
+    shared: Arc<Shared>,
+}
+
+impl SiemExporter {
+    /// Validate the configuration and start the delivery task.
+    pub fn start(config: SiemConfig) -> Result<Self> {
+        config.validate()?;
+        let transport = Transport::new(&config)?;
+        let shared = Arc::new(Shared {
+            mapping: FieldMapping::new(config.field_map.clone(), config.static_fields.clone()),
+            events: config.events.iter().cloned().collect(),
+            queue: Mutex::new(VecDeque::new()),
+            counters: Mutex::new(Counters::default()),
+            wake: Notify::new(),
+            config,
+        });
+        tokio::spawn(deliver(Arc::clone(&shared), transport));
+        Ok(Self { shared })
+    }
+
+    /// Queue an audit event for export.
+    pub fn push(&self, event: &AuditEvent) {
+        if !self.shared.events.is_empty() && !self.shared.events.contains(&event.event) {
+            return;
+        }
+        let Ok(body) = serde_json::to_value(event) else {
+            return;
+        };
+        let queued = QueuedEvent {
+            name: event.event.clone(),
+            timestamp: event.timestamp.clone(),
+            body: self.shared.mapping.apply(body),
+        };
+        let full_batch = {
+            let mut queue = self.shared.queue.lock().unwrap();
+            queue.push_back(queued);
+            self.shared.trim(&mut queue);
+            queue.len() >= self.shared.config.batch_size
+        };
+        if full_batch {
+            self.shared.wake.notify_one();
+        }
+    }
+
+    pub fn health(&self) -> ExportHealth {
+        let config = &self.shared.config;
+        let queued = self.shared.queue.lock().unwrap().len();
+        let counters = self.shared.counters.lock().unwrap();
+        let destination = match config.transport {
+            SiemTransportKind::Syslog => config.syslog.address.clone(),
+            SiemTransportKind::Http => reqwest::Url::parse(&config.http.url)
+                .map(|mut url| {
+                    url.set_query(None);
+                    let _ = url.set_password(None);
+                    url.to_string()
+                })
+                .unwrap_or_default(),
+        };
+        ExportHealth {
+            enabled: true,
+            healthy: counters.consecutive_failures == 0,
+            transport: Some(config.transport),
+            destination: Some(destination),
+            queued,
+            buffer_size: config.buffer_size,
+            sent_total: counters.sent_total,
+            dropped_total: counters.dropped_total,
+            failed_deliveries: counters.failed_deliveries,
+            consecutive_failures: counters.consecutive_failures,
+            last_success_at: counters.last_success_at,
+            last_error: counters.last_error.clone(),
+            last_error_at: counters.last_error_at,
+        }
+    }
+}
+
+/// Delivery loop: send batches as they fill up or the flush interval
+/// passes; on failure put the batch back and retry with backoff.
+async fn deliver(shared: Arc<Shared>, mut transport: Transport) {
+    let config = &shared.config;
+    let flush_interval = Duration::from_millis(config.flush_interval_ms.max(10));
+    let min_backoff = Duration::from_millis(config.retry_backoff_ms.max(10));
+    let max_backoff = Duration::from_millis(config.max_retry_backoff_ms).max(min_backoff);
+    let mut backoff = min_backoff;
+
+    loop {
+        let pending = shared.queue.lock().unwrap().len();
+        if pending < config.batch_size {
+            tokio::select! {
+                _ = shared.wake.notified() => {}
+                _ = tokio::time::sleep(flush_interval) => {}
+            }
+        }
+        let batch: Vec<QueuedEvent> = {
+            let mut queue = shared.queue.lock().unwrap();
+            let n = queue.len().min(config.batch_size);
+            queue.drain(..n).collect()
+        };
+        if batch.is_empty() {
+            continue;
+        }
+
+        match transport.send(&batch).await {
+            Ok(()) => {
+                let mut counters = shared.counters.lock().unwrap();
+                if counters.consecutive_failures > 0 {
+                    info!(
+                        "SIEM export recovered after {} failed deliveries",
+                        counters.consecutive_failures
+                    );
+                }
+                counters.sent_total += batch.len() as u64;
+                counters.consecutive_failures = 0;
+                counters.last_success_at = Some(Utc::now());
+                backoff = min_backoff;
+            }
+            Err(e) => {
+                {
+                    let mut queue = shared.queue.lock().unwrap();
+                    for event in batch.into_iter().rev() {
+                        queue.push_front(event);
+                    }
+                    shared.trim(&mut queue);
+                }
+                {
+                    let mut counters = shared.counters.lock().unwrap();
+                    if counters.consecutive_failures == 0 {
+                        warn!("SIEM export failing, buffering events: {:#}", e);
+                    }
+                    counters.failed_deliveries += 1;
+                    counters.consecutive_failures += 1;
+                    counters.last_error = Some(format!("{e:#}"));
+                    counters.last_error_at = Some(Utc::now());
+                }
+                tokio::time::sleep(backoff).await;
+                backoff = (backoff * 2).min(max_backoff);
+            }
+        }
+    }
+}
@@ -0,0 +1,302 @@
+//! Delivery of event batches to the collector.
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::{Context, Result, bail};
+use tokio::io::{AsyncWrite, AsyncWriteExt};
+use tokio::net::TcpStream;
+use tokio_rustls::TlsConnector;
+use tokio_rustls::rustls::pki_types::pem::PemObject;
+use tokio_rustls::rustls::pki_types::{CertificateDer, ServerName};
+use tokio_rustls::rustls::{ClientConfig, RootCertStore};
+
+use super::QueuedEvent;
+use super::config::{
+    HttpBatchFormat, HttpExportConfig, SiemConfig, SiemTransportKind, SyslogConfig, facility_code,
+};
+
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+const WRITE_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Syslog severities used for audit events.
+const SEVERITY_WARNING: u8 = 4;
+const SEVERITY_INFO: u8 = 6;
+
+pub(crate) enum Transport {
+    Syslog(SyslogTransport),
+    Http(HttpTransport),
+}
+
+impl Transport {
+    pub(crate) fn new(config: &SiemConfig) -> Result<Self> {
+        Ok(match config.transport {
+            SiemTransportKind::Syslog => Self::Syslog(SyslogTransport::new(&config.syslog)?),
+            SiemTransportKind::Http => Self::Http(HttpTransport::new(&config.http)?),
+        })
+    }
+
+    pub(crate) async fn send(&mut self, batch: &[QueuedEvent]) -> Result<()> {
+        match self {
+            Self::Syslog(t) => t.send(batch).await,
+            Self::Http(t) => t.send(batch).await,
+        }
+    }
+}
+
+type Stream = Box<dyn AsyncWrite + Send + Unpin>;
+
+pub(crate) struct SyslogTransport {
+    address: String,
+    tls: Option<(TlsConnector, ServerName<'static>)>,
+    facility: u8,
+    hostname: String,
+    app_name: String,
+    stream: Option<Stream>,
+}
+
+impl SyslogTransport {
+    fn new(config: &SyslogConfig) -> Result<Self> {
+        let facility = facility_code(&config.facility)
+            .with_context(|| format!("unknown syslog facility '{}'", config.facility))?;
+        let tls = if config.tls {
+            let name = match &config.server_name {
+                Some(name) => name.clone(),
+                None => host_of(&config.address).to_string(),
+            };
+            let server_name = ServerName::try_from(name.clone())
+                .with_context(|| format!("invalid TLS server name '{name}'"))?;
+            Some((tls_connector(config)?, server_name))
+        } else {
+            None
+        };
+        Ok(Self {
+            address: config.address.clone(),
+            tls,
+            facility,
+            hostname: sd_token(&config.hostname.clone().unwrap_or_else(local_hostname), 255),
+            app_name: sd_token(&config.app_name, 48),
+            stream: None,
+        })
+    }
+
+    async fn connect(&self) -> Result<Stream> {
+        let tcp = tokio::time::timeout(CONNECT_TIMEOUT, TcpStream::connect(&self.address))
+            .await
+            .with_context(|| format!("connecting to {} timed out", self.address))?
+            .with_context(|| format!("connecting to {}", self.address))?;
+        tcp.set_nodelay(true).ok();
+        Ok(match &self.tls {
+            Some((connector, server_name)) => {
+                let tls = tokio::time::timeout(
+                    CONNECT_TIMEOUT,
+                    connector.connect(server_name.clone(), tcp),
+                )
+                .await
+                .context("TLS handshake timed out")?
+                .context("TLS handshake")?;
+                Box::new(tls)
+            }
+            None => Box::new(tcp),
+        })
+    }
+
+    async fn send(&mut self, batch: &[QueuedEvent]) -> Result<()> {
+        let mut frames = Vec::new();
+        for event in batch {
+            let message = self.format(event);
+            // RFC 6587 octet counting.
+            frames.extend_from_slice(format!("{} ", message.len()).as_bytes());
+            frames.extend_from_slice(message.as_bytes());
+        }
+        let mut stream = match self.stream.take() {
+            Some(stream) => stream,
+            None => self.connect().await?,
+        };
+        let written = tokio::time::timeout(WRITE_TIMEOUT, async {
+            stream.write_all(&frames).await?;
+            stream.flush().await
+        })
+        .await
+        .context("syslog write timed out")?;
+        // A failed write drops the connection; the next attempt reconnects.
+        written.with_context(|| format!("writing to {}", self.address))?;
+        self.stream = Some(stream);
+        Ok(())
+    }
+
+    /// RFC 5424 message with the mapped event as JSON body.
+    fn format(&self, event: &QueuedEvent) -> String {
+        let severity = if event.name.ends_with("_failed") || event.name.ends_with("_denied") {
+            SEVERITY_WARNING
+        } else {
+            SEVERITY_INFO
+        };
+        format!(
+            "<{}>1 {} {} {} {} {} - {}",
+            u16::from(self.facility) * 8 + u16::from(severity),
+            event.timestamp,
+            self.hostname,
+            self.app_name,
+            std::process::id(),
+            sd_token(&event.name, 32),
+            event.body
+        )
+    }
+}
+
+fn tls_connector(config: &SyslogConfig) -> Result<TlsConnector> {
+    let mut roots = RootCertStore::empty();
+    match &config.ca_file {
+        Some(path) => {
+            for cert in CertificateDer::pem_file_iter(path)
+                .with_context(|| format!("reading {}", path.display()))?
+            {
+                let cert = cert.with_context(|| format!("parsing {}", path.display()))?;
+                roots.add(cert).context("adding CA certificate")?;
+            }
+            if roots.is_empty() {
+                bail!("no certificates in {}", path.display());
+            }
+        }
+        None => roots.extend(webpki_roots::TLS_SERVER_ROOTS.iter().cloned()),
+    }
+    let provider = Arc::new(tokio_rustls::rustls::crypto::ring::default_provider());
+    let tls = ClientConfig::builder_with_provider(provider)
+        .with_safe_default_protocol_versions()
+        .context("configuring TLS")?
+        .with_root_certificates(roots)
+        .with_no_client_auth();
+    Ok(TlsConnector::from(Arc::new(tls)))
+}
+
+pub(crate) struct HttpTransport {
+    client: reqwest::Client,
+    url: String,
+    format: HttpBatchFormat,
+}
+
+impl HttpTransport {
+    fn new(config: &HttpExportConfig) -> Result<Self> {
+        let mut headers = reqwest::header::HeaderMap::new();
+        for (name, value) in &config.headers {
+            headers.insert(
+                reqwest::header::HeaderName::from_bytes(name.as_bytes())
+                    .with_context(|| format!("invalid header name '{name}'"))?,
+                reqwest::header::HeaderValue::from_str(value)
+                    .with_context(|| format!("invalid value for header '{name}'"))?,
+            );
+        }
+        let client = reqwest::Client::builder()
+            .timeout(Duration::from_secs(config.timeout_secs.max(1)))
+            .default_headers(headers)
+            .build()
+            .context("building SIEM HTTP client")?;
+        Ok(Self {
+            client,
+            url: config.url.clone(),
+            format: config.format,
+        })
+    }
+
+    async fn send(&mut self, batch: &[QueuedEvent]) -> Result<()> {
+        let (content_type, body) = match self.format {
+            HttpBatchFormat::Ndjson => {
+                let mut body = String::new();
+                for event in batch {
+                    body.push_str(&event.body.to_string());
+                    body.push('\n');
+                }
+                ("application/x-ndjson", body)
+            }
+            HttpBatchFormat::JsonArray => {
+                let events: Vec<_> = batch.iter().map(|e| &e.body).collect();
+                ("application/json", serde_json::to_string(&events)?)
+            }
+        };
+        let response = self
+            .client
+            .post(&self.url)
+            .header(reqwest::header::CONTENT_TYPE, content_type)
+            .body(body)
+            .send()
+            .await
+            .context("posting events")?;
+        let status = response.status();
+        if !status.is_success() {
+            let detail = response.text().await.unwrap_or_default();
+            bail!(
+                "collector answered {status}: {}",
+                detail.chars().take(200).collect::<String>()
+            );
+        }
+        Ok(())
+    }
+}
+
+/// Host part of `host:port` (brackets stripped for IPv6).
+fn host_of(address: &str) -> &str {
+    let host = match address.rsplit_once(':') {
+        Some((host, port)) if port.bytes().all(|b| b.is_ascii_digit()) => host,
+        _ => address,
+    };
+    host.trim_start_matches('[').trim_end_matches(']')
+}
+
+/// Printable-ASCII header field without spaces; `-` when empty.
+fn sd_token(value: &str, max: usize) -> String {
+    let token: String = value
+        .chars()
+        .filter(|c| c.is_ascii_graphic())
+        .take(max)
+        .collect();
+    if token.is_empty() {
+        "-".to_string()
+    } else {
+        token
+    }
+}
+
+pub(crate) fn local_hostname() -> String {
+    let mut buf = [0u8; 256];
+    // SAFETY: the buffer is valid for its length; gethostname NUL-terminates
+    // on success (truncation is handled by the length scan below).
+    let rc = unsafe { libc::gethostname(buf.as_mut_ptr().cast(), buf.len()) };
+    if rc != 0 {
+        return "-".to_string();
+    }
+    let len = buf.iter().position(|b| *b == 0).unwrap_or(buf.len());
+    String::from_utf8_lossy(&buf[..len]).into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn syslog_messages_follow_rfc5424() {
+        let transport = SyslogTransport::new(&SyslogConfig {
+            address: "siem.example.com:6514".to_string(),
+            tls: false,
+            hostname: Some("oqto host".to_string()),
+            ..SyslogConfig::default()
+        })
+        .unwrap();
+        let event = QueuedEvent {
+            name: "auth_login_failed".to_string(),
+            timestamp: "2026-05-01T12:00:00.000Z".to_string(),
+            body: json!({"event": "auth_login_failed"}),
+        };
+        assert_eq!(
+            transport.format(&event),
+            format!(
+                "<36>1 2026-05-01T12:00:00.000Z oqtohost oqto {} auth_login_failed - \
+                 {{\"event\":\"auth_login_failed\"}}",
+                std::process::id()
+            )
+        );
+        assert_eq!(host_of("[::1]:6514"), "::1");
+        assert_eq!(host_of("siem.example.com:6514"), "siem.example.com");
+    }
+}
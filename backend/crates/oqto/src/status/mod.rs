@@ -0,0 +1,399 @@
+//! Public status page.
+//!
+//! Probes coarse component health on a fixed interval, keeps the probe
+//! history for uptime percentages, and combines it with admin-authored
+//! incident notes into the unauthenticated `GET /api/status` report.
+
+mod models;
+mod repository;
+
+pub use models::{
+    ComponentReport, ComponentStatus, CreateIncidentRequest, Incident, IncidentListQuery,
+    IncidentSeverity, StatusPageConfig, StatusReport, UpdateIncidentRequest, UptimeSummary,
+};
+pub use repository::{ProbeWindow, StatusRepository};
+
+use std::net::IpAddr;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use chrono::{DateTime, NaiveDateTime, Utc};
+use dashmap::DashMap;
+use tokio::sync::{Mutex, RwLock};
+use tracing::{debug, warn};
+
+use crate::db::DbHealth;
+use crate::eavs::EavsClient;
+
+/// Timestamp format used in the status tables (matches SQLite `datetime('now')`).
+const TIMESTAMP_FORMAT: &str = "%Y-%m-%d %H:%M:%S";
+/// Upper bound on a single component probe.
+const PROBE_TIMEOUT: Duration = Duration::from_secs(5);
+/// How often old probe samples are pruned.
+const MAINTENANCE_INTERVAL: Duration = Duration::from_secs(3600);
+/// Tracked client IPs before stale rate limit windows are pruned.
+const MAX_TRACKED_CLIENTS: usize = 10_000;
+/// Rate limit window length.
+const RATE_LIMIT_WINDOW: Duration = Duration::from_secs(60);
+
+/// Status page service: probes, uptime and incidents.
+pub struct StatusService {
+    repo: StatusRepository,
+    config: StatusPageConfig,
+    db_health: Arc<DbHealth>,
+    eavs: Option<Arc<EavsClient>>,
+    /// Most recent probe results.
+    latest: RwLock<Vec<(&'static str, ComponentStatus)>>,
+    /// Last computed report and when it was computed.
+    cache: Mutex<Option<(Instant, StatusReport)>>,
+    /// Per-client request counts for the current rate limit window.
+    clients: DashMap<IpAddr, (Instant, u32)>,
+}
+
+impl StatusService {
+    pub fn new(
+        repo: StatusRepository,
+        config: StatusPageConfig,
+        db_health: Arc<DbHealth>,
+        eavs: Option<Arc<EavsClient>>,
+    ) -> Self {
+        Self {
+            repo,
+            config,
+            db_health,
+            eavs,
+            latest: RwLock::new(Vec::new()),
+            cache: Mutex::new(None),
+            clients: DashMap::new(),
+        }
+    }
+
+    pub fn repository(&self) -> &StatusRepository {
+        &self.repo
+    }
+
+    pub fn config(&self) -> &StatusPageConfig {
+        &self.config
+    }
+
+    /// Record a request from `ip`. Returns false once the client exceeded
+    /// `rate_limit_per_minute` in the current window.
+    pub fn check_rate_limit(&self, ip: IpAddr) -> bool {
+        let now = Instant::now();
+        if self.clients.len() >= MAX_TRACKED_CLIENTS {
+            self.clients
+                .retain(|_, (start, _)| now.duration_since(*start) < RATE_LIMIT_WINDOW);
+        }
+        let mut entry = self.clients.entry(ip).or_insert((now, 0));
+        let (start, count) = entry.value_mut();
+        if now.duration_since(*start) >= RATE_LIMIT_WINDOW {
+            *start = now;
+            *count = 0;
+        }
+        if *count >= self.config.rate_limit_per_minute {
+            return false;
+        }
+        *count += 1;
+        true
+    }
+
+    /// Drop the cached report so incident changes show up immediately.
+    pub async fn invalidate(&self) {
+        *self.cache.lock().await = None;
+    }
+
+    /// Current status report, reusing a recent one within `cache_ttl_secs`.
+    pub async fn report(&self) -> anyhow::Result<StatusReport> {
+        let mut cache = self.cache.lock().await;
+        if let Some((computed, report)) = cache.as_ref()
+            && computed.elapsed() < Duration::from_secs(self.config.cache_ttl_secs)
+        {
+            return Ok(report.clone());
+        }
+
+        let report = self.compute_report().await?;
+        *cache = Some((Instant::now(), report.clone()));
+        Ok(report)
+    }
+
+    async fn compute_report(&self) -> anyhow::Result<StatusReport> {
+        let mut latest = self.latest.read().await.clone();
+        if latest.is_empty() {
+            latest = self.probe().await;
+        }
+
+        let now = Utc::now();
+        let mut components = Vec::with_capacity(latest.len());
+        for (name, status) in latest {
+            components.push(ComponentReport {
+                name: name.to_string(),
+                status,
+                uptime: self.uptime(name, now).await?,
+            });
+        }
+
+        let incidents: Vec<Incident> = self
+            .repo
+            .list_incidents(&IncidentListQuery::default())
+            .await?
+            .into_iter()
+            .map(Incident::public)
+            .collect();
+
+        for incident in &incidents {
+            let implied = incident.severity.implied_status();
+            match incident.component.as_deref() {
+                Some(name) => {
+                    if let Some(component) = components.iter_mut().find(|c| c.name == name) {
+                        component.status = component.status.max(implied);
+                    }
+                }
+                None => {
+                    for component in &mut components {
+                        component.status = component.status.max(implied);
+                    }
+                }
+            }
+        }
+
+        let status = components
+            .iter()
+            .map(|c| c.status)
+            .max()
+            .unwrap_or(ComponentStatus::Operational);
+
+        Ok(StatusReport {
+            status,
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            checked_at: now.to_rfc3339(),
+            components,
+            incidents,
+        })
+    }
+
+    async fn uptime(&self, component: &str, now: DateTime<Utc>) -> anyhow::Result<UptimeSummary> {
+        let mut windows = [None; 4];
+        for (slot, days) in windows.iter_mut().zip([1, 7, 30, 90]) {
+            if days > self.config.history_days.max(1) {
+                continue;
+            }
+            let start = now - chrono::Duration::days(days);
+            let window = self
+                .repo
+                .probe_window(component, &start.format(TIMESTAMP_FORMAT).to_string())
+                .await?;
+            *slot = uptime_percent(&window, start, now, self.config.probe_interval_secs);
+        }
+        let [day, week, month, quarter] = windows;
+        Ok(UptimeSummary {
+            day,
+            week,
+            month,
+            quarter,
+        })
+    }
+
+    /// Probe all components once.
+    pub async fn probe(&self) -> Vec<(&'static str, ComponentStatus)> {
+        // Answering at all means the API is up.
+        let mut results = vec![("api", ComponentStatus::Operational)];
+
+        let database = match tokio::time::timeout(PROBE_TIMEOUT, self.repo.ping()).await {
+            Ok(Ok(())) if self.db_health.is_degraded() => ComponentStatus::Degraded,
+            Ok(Ok(())) => ComponentStatus::Operational,
+            Ok(Err(e)) => {
+                warn!("Status probe: database unavailable: {:#}", e);
+                ComponentStatus::Down
+            }
+            Err(_) => ComponentStatus::Down,
+        };
+        results.push(("database", database));
+
+        if let Some(eavs) = &self.eavs {
+            let llm = match tokio::time::timeout(PROBE_TIMEOUT, eavs.health_check()).await {
+                Ok(Ok(true)) => ComponentStatus::Operational,
+                Ok(Ok(false)) => ComponentStatus::Degraded,
+                Ok(Err(e)) => {
+                    debug!("Status probe: LLM proxy unavailable: {}", e);
+                    ComponentStatus::Down
+                }
+                Err(_) => ComponentStatus::Down,
+            };
+            results.push(("llm_proxy", llm));
+        }
+
+        results
+    }
+
+    /// Probe on `probe_interval_secs` and persist the results.
+    pub fn start_probe_task(self: &Arc<Self>) {
+        let service = Arc::clone(self);
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(Duration::from_secs(
+                service.config.probe_interval_secs.max(5),
+            ));
+            interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+            let mut last_prune: Option<Instant> = None;
+            loop {
+                interval.tick().await;
+
+                let results = service.probe().await;
+                let checked_at = Utc::now().format(TIMESTAMP_FORMAT).to_string();
+                for (component, status) in &results {
+                    if let Err(e) = service
+                        .repo
+                        .record_probe(component, *status, &checked_at)
+                        .await
+                    {
+                        warn!(component, "Failed to record status probe: {:#}", e);
+                    }
+                }
+                *service.latest.write().await = results;
+
+                if last_prune.is_none_or(|at| at.elapsed() >= MAINTENANCE_INTERVAL) {
+                    last_prune = Some(Instant::now());
+                    match service.repo.prune_probes(service.config.history_days).await {
+                        Ok(0) => {}
+                        Ok(n) => debug!("Pruned {} status probe samples", n),
+                        Err(e) => warn!("Failed to prune status probes: {:#}", e),
+                    }
+                }
+            }
+        });
+    }
+}
+
+/// Uptime over `[start, now]` as a percentage.
+///
+/// Intervals without any sample (the server itself was down) count as
+/// downtime. The window starts at the first recorded sample so a fresh install
+/// is not penalised for history it never had. Returns None without samples.
+fn uptime_percent(
+    window: &ProbeWindow,
+    start: DateTime<Utc>,
+    now: DateTime<Utc>,
+    interval_secs: u64,
+) -> Option<f64> {
+    let first = window.first_checked_at.as_deref()?;
+    let first = NaiveDateTime::parse_from_str(first, TIMESTAMP_FORMAT)
+        .ok()?
+        .and_utc()
+        .max(start);
+    let span = (now - first).num_seconds().max(0) as f64;
+    let expected = (span / interval_secs.max(1) as f64).floor() + 1.0;
+    let expected = expected.max(window.total as f64);
+    Some((window.up as f64 / expected * 100.0).clamp(0.0, 100.0))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(ts: &str) -> DateTime<Utc> {
+        NaiveDateTime::parse_from_str(ts, TIMESTAMP_FORMAT)
+            .unwrap()
+            .and_utc()
+    }
+
+    #[test]
+    fn uptime_counts_missing_samples_as_down() {
+        let window = ProbeWindow {
+            up: 5,
+            total: 5,
+            first_checked_at: Some("2026-05-10 10:00:00".to_string()),
+        };
+        // 10 minutes at 60s intervals = 11 expected samples.
+        let pct = uptime_percent(
+            &window,
+            at("2026-05-09 10:10:00"),
+            at("2026-05-10 10:10:00"),
+            60,
+        )
+        .unwrap();
+        assert!((pct - 5.0 / 11.0 * 100.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn uptime_is_full_when_every_sample_is_up() {
+        let window = ProbeWindow {
+            up: 3,
+            total: 3,
+            first_checked_at: Some("2026-05-10 10:00:00".to_string()),
+        };
+        let pct = uptime_percent(
+            &window,
+            at("2026-05-03 10:02:00"),
+            at("2026-05-10 10:02:00"),
+            60,
+        );
+        assert_eq!(pct, Some(100.0));
+        assert_eq!(
+            uptime_percent(
+                &ProbeWindow {
+                    up: 0,
+                    total: 0,
+                    first_checked_at: None,
+                },
+                at("2026-05-03 10:02:00"),
+                at("2026-05-10 10:02:00"),
+                60,
+            ),
+            None
+        );
+    }
+
+    #[tokio::test]
+    async fn rate_limit_blocks_after_quota() {
+        let db = crate::db::Database::in_memory().await.unwrap();
+        let service = StatusService::new(
+            StatusRepository::new(db.pool().clone()),
+            StatusPageConfig {
+                rate_limit_per_minute: 2,
+                ..Default::default()
+            },
+            Arc::new(DbHealth::default()),
+            None,
+        );
+        let ip: IpAddr = "203.0.113.7".parse().unwrap();
+        assert!(service.check_rate_limit(ip));
+        assert!(service.check_rate_limit(ip));
+        assert!(!service.check_rate_limit(ip));
+        assert!(service.check_rate_limit("203.0.113.8".parse().unwrap()));
+    }
+
+    #[tokio::test]
+    async fn report_applies_active_incidents() {
+        let db = crate::db::Database::in_memory().await.unwrap();
+        let service = StatusService::new(
+            StatusRepository::new(db.pool().clone()),
+            StatusPageConfig::default(),
+            Arc::new(DbHealth::default()),
+            None,
+        );
+        let report = service.report().await.unwrap();
+        assert_eq!(report.status, ComponentStatus::Operational);
+        assert_eq!(report.components.len(), 2);
+
+        service
+            .repository()
+            .create_incident(
+                &CreateIncidentRequest {
+                    title: "Database maintenance".to_string(),
+                    message: String::new(),
+                    severity: IncidentSeverity::Critical,
+                    component: Some("database".to_string()),
+                },
+                "admin",
+            )
+            .await
+            .unwrap();
+        service.invalidate().await;
+
+        let report = service.report().await.unwrap();
+        assert_eq!(report.status, ComponentStatus::Down);
+        let api = report.components.iter().find(|c| c.name == "api").unwrap();
+        assert_eq!(api.status, ComponentStatus::Operational);
+        assert_eq!(report.incidents.len(), 1);
+        assert!(report.incidents[0].created_by.is_none());
+    }
+}
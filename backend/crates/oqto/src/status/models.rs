@@ -0,0 +1,154 @@
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+
+/// Public status page configuration.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct StatusPageConfig {
+    /// Serve `GET /api/status` and record health probes.
+    pub enabled: bool,
+    /// Seconds between health probes.
+    pub probe_interval_secs: u64,
+    /// Days of probe history kept for uptime calculation.
+    pub history_days: i64,
+    /// Requests per minute allowed from a single client IP.
+    pub rate_limit_per_minute: u32,
+    /// Seconds a computed status response is reused.
+    pub cache_ttl_secs: u64,
+}
+
+impl Default for StatusPageConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            probe_interval_secs: 60,
+            history_days: 90,
+            rate_limit_per_minute: 30,
+            cache_ttl_secs: 10,
+        }
+    }
+}
+
+/// Coarse health of a component.
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize, sqlx::Type,
+)]
+#[serde(rename_all = "snake_case")]
+#[sqlx(rename_all = "snake_case")]
+pub enum ComponentStatus {
+    Operational,
+    Degraded,
+    Down,
+}
+
+/// Incident impact shown on the status page.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, sqlx::Type)]
+#[serde(rename_all = "snake_case")]
+#[sqlx(rename_all = "snake_case")]
+pub enum IncidentSeverity {
+    /// Planned work.
+    Maintenance,
+    #[default]
+    Minor,
+    Major,
+    Critical,
+}
+
+impl IncidentSeverity {
+    /// Component status implied by an active incident of this severity.
+    pub fn implied_status(self) -> ComponentStatus {
+        match self {
+            Self::Maintenance | Self::Minor | Self::Major => ComponentStatus::Degraded,
+            Self::Critical => ComponentStatus::Down,
+        }
+    }
+}
+
+/// Admin-authored incident note.
+#[derive(Debug, Clone, Serialize, FromRow)]
+pub struct Incident {
+    pub id: String,
+    pub title: String,
+    pub message: String,
+    pub severity: IncidentSeverity,
+    /// Affected component, or None for the whole service.
+    pub component: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub created_by: Option<String>,
+    pub created_at: String,
+    pub updated_at: String,
+    pub resolved_at: Option<String>,
+}
+
+impl Incident {
+    /// Copy suitable for unauthenticated readers.
+    pub fn public(mut self) -> Self {
+        self.created_by = None;
+        self
+    }
+}
+
+/// Request body for creating an incident.
+#[derive(Debug, Clone, Deserialize)]
+pub struct CreateIncidentRequest {
+    pub title: String,
+    #[serde(default)]
+    pub message: String,
+    #[serde(default)]
+    pub severity: IncidentSeverity,
+    #[serde(default)]
+    pub component: Option<String>,
+}
+
+/// Request body for updating an incident. Omitted fields are left unchanged.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct UpdateIncidentRequest {
+    pub title: Option<String>,
+    pub message: Option<String>,
+    pub severity: Option<IncidentSeverity>,
+    pub component: Option<String>,
+    /// Mark resolved (`true`) or reopen (`false`).
+    pub resolved: Option<bool>,
+}
+
+/// Query for listing incidents.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct IncidentListQuery {
+    /// Include resolved incidents.
+    #[serde(default)]
+    pub include_resolved: bool,
+    #[serde(default)]
+    pub limit: Option<i64>,
+}
+
+/// Uptime percentages over the standard windows (None without samples).
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct UptimeSummary {
+    #[serde(rename = "24h")]
+    pub day: Option<f64>,
+    #[serde(rename = "7d")]
+    pub week: Option<f64>,
+    #[serde(rename = "30d")]
+    pub month: Option<f64>,
+    #[serde(rename = "90d")]
+    pub quarter: Option<f64>,
+}
+
+/// Health of one component on the status page.
+#[derive(Debug, Clone, Serialize)]
+pub struct ComponentReport {
+    pub name: String,
+    pub status: ComponentStatus,
+    pub uptime: UptimeSummary,
+}
+
+/// `GET /api/status` response.
+#[derive(Debug, Clone, Serialize)]
+pub struct StatusReport {
+    /// Worst status across components and active incidents.
+    pub status: ComponentStatus,
+    pub version: String,
+    pub checked_at: String,
+    pub components: Vec<ComponentReport>,
+    pub incidents: Vec<Incident>,
+}
@@ -0,0 +1,314 @@
+use anyhow::{Context, Result};
+use sqlx::{QueryBuilder, Sqlite, SqlitePool};
+
+use super::{
+    ComponentStatus, CreateIncidentRequest, Incident, IncidentListQuery, UpdateIncidentRequest,
+};
+
+const DEFAULT_INCIDENT_LIMIT: i64 = 50;
+const MAX_INCIDENT_LIMIT: i64 = 500;
+
+/// Probe samples for one component within a window.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProbeWindow {
+    /// Samples where the component was operational or degraded.
+    pub up: i64,
+    /// All samples in the window.
+    pub total: i64,
+    /// Oldest sample in the window.
+    pub first_checked_at: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct StatusRepository {
+    pool: SqlitePool,
+}
+
+impl StatusRepository {
+    pub fn new(pool: SqlitePool) -> Self {
+        Self { pool }
+    }
+
+    fn generate_id() -> String {
+        format!("inc_{}", nanoid::nanoid!(12))
+    }
+
+    /// Cheap round-trip to check the database answers.
+    pub async fn ping(&self) -> Result<()> {
+        sqlx::query("SELECT 1")
+            .execute(&self.pool)
+            .await
+            .context("ping database")?;
+        Ok(())
+    }
+
+    /// Record one probe result.
+    pub async fn record_probe(
+        &self,
+        component: &str,
+        status: ComponentStatus,
+        checked_at: &str,
+    ) -> Result<()> {
+        sqlx::query(
+            r#"INSERT OR REPLACE INTO status_probes (component, status, checked_at) VALUES (?, ?, ?)"#,
+        )
+        .bind(component)
+        .bind(status)
+        .bind(checked_at)
+        .execute(&self.pool)
+        .await
+        .context("insert status probe")?;
+        Ok(())
+    }
+
+    /// Probe samples for `component` recorded at or after `since`.
+    pub async fn probe_window(&self, component: &str, since: &str) -> Result<ProbeWindow> {
+        let (up, total, first_checked_at) = sqlx::query_as::<_, (i64, i64, Option<String>)>(
+            r#"SELECT COALESCE(SUM(CASE WHEN status != 'down' THEN 1 ELSE 0 END), 0),
+                      COUNT(*),
+                      MIN(checked_at)
+               FROM status_probes WHERE component = ? AND checked_at >= ?"#,
+        )
+        .bind(component)
+        .bind(since)
+        .fetch_one(&self.pool)
+        .await
+        .context("query status probe window")?;
+        Ok(ProbeWindow {
+            up,
+            total,
+            first_checked_at,
+        })
+    }
+
+    /// Drop probe samples older than `history_days`.
+    pub async fn prune_probes(&self, history_days: i64) -> Result<u64> {
+        let result =
+            sqlx::query(r#"DELETE FROM status_probes WHERE checked_at < datetime('now', ?)"#)
+                .bind(format!("-{history_days} days"))
+                .execute(&self.pool)
+                .await
+                .context("prune status probes")?;
+        Ok(result.rows_affected())
+    }
+
+    /// List incidents, newest first.
+    pub async fn list_incidents(&self, query: &IncidentListQuery) -> Result<Vec<Incident>> {
+        let mut qb = QueryBuilder::<Sqlite>::new(
+            r#"SELECT id, title, message, severity, component, created_by, created_at, updated_at, resolved_at
+               FROM status_incidents"#,
+        );
+        if !query.include_resolved {
+            qb.push(" WHERE resolved_at IS NULL");
+        }
+        let limit = query
+            .limit
+            .unwrap_or(DEFAULT_INCIDENT_LIMIT)
+            .clamp(1, MAX_INCIDENT_LIMIT);
+        qb.push(" ORDER BY created_at DESC, id DESC LIMIT ")
+            .push_bind(limit);
+
+        qb.build_query_as::<Incident>()
+            .fetch_all(&self.pool)
+            .await
+            .context("list status incidents")
+    }
+
+    pub async fn get_incident(&self, id: &str) -> Result<Option<Incident>> {
+        sqlx::query_as::<_, Incident>(
+            r#"SELECT id, title, message, severity, component, created_by, created_at, updated_at, resolved_at
+               FROM status_incidents WHERE id = ?"#,
+        )
+        .bind(id)
+        .fetch_optional(&self.pool)
+        .await
+        .context("get status incident")
+    }
+
+    pub async fn create_incident(
+        &self,
+        request: &CreateIncidentRequest,
+        created_by: &str,
+    ) -> Result<Incident> {
+        let id = Self::generate_id();
+        sqlx::query(
+            r#"INSERT INTO status_incidents (id, title, message, severity, component, created_by)
+               VALUES (?, ?, ?, ?, ?, ?)"#,
+        )
+        .bind(&id)
+        .bind(&request.title)
+        .bind(&request.message)
+        .bind(request.severity)
+        .bind(&request.component)
+        .bind(created_by)
+        .execute(&self.pool)
+        .await
+        .context("insert status incident")?;
+
+        self.get_incident(&id)
+            .await?
+            .ok_or_else(|| anyhow::anyhow!("Incident not found after creation"))
+    }
+
+    /// Apply a partial update. Returns None if the incident does not exist.
+    pub async fn update_incident(
+        &self,
+        id: &str,
+        request: &UpdateIncidentRequest,
+    ) -> Result<Option<Incident>> {
+        let mut qb =
+            QueryBuilder::<Sqlite>::new("UPDATE status_incidents SET updated_at = datetime('now')");
+        if let Some(title) = &request.title {
+            qb.push(", title = ").push_bind(title.clone());
+        }
+        if let Some(message) = &request.message {
+            qb.push(", message = ").push_bind(message.clone());
+        }
+        if let Some(severity) = request.severity {
+            qb.push(", severity = ").push_bind(severity);
+        }
+        if let Some(component) = &request.component {
+            // An empty string clears the component (whole-service incident).
+            let component = Some(component.clone()).filter(|c| !c.is_empty());
+            qb.push(", component = ").push_bind(component);
+        }
+        match request.resolved {
+            Some(true) => {
+                qb.push(", resolved_at = COALESCE(resolved_at, datetime('now'))");
+            }
+            Some(false) => {
+                qb.push(", resolved_at = NULL");
+            }
+            None => {}
+        }
+        qb.push(" WHERE id = ").push_bind(id.to_string());
+
+        let updated = qb
+            .build()
+            .execute(&self.pool)
+            .await
+            .context("update status incident")?
+            .rows_affected();
+        if updated == 0 {
+            return Ok(None);
+        }
+        self.get_incident(id).await
+    }
+
+    /// Delete an incident. Returns false if it did not exist.
+    pub async fn delete_incident(&self, id: &str) -> Result<bool> {
+        let result = sqlx::query("DELETE FROM status_incidents WHERE id = ?")
+            .bind(id)
+            .execute(&self.pool)
+            .await
+            .context("delete status incident")?;
+        Ok(result.rows_affected() > 0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::Database;
+    use crate::status::IncidentSeverity;
+
+    #[tokio::test]
+    async fn test_probe_window_counts_up_samples() {
+        let db = Database::in_memory().await.unwrap();
+        let repo = StatusRepository::new(db.pool().clone());
+
+        for (ts, status) in [
+            ("2026-05-10 10:00:00", ComponentStatus::Operational),
+            ("2026-05-10 10:01:00", ComponentStatus::Degraded),
+            ("2026-05-10 10:02:00", ComponentStatus::Down),
+            ("2026-05-09 10:00:00", ComponentStatus::Down),
+        ] {
+            repo.record_probe("database", status, ts).await.unwrap();
+        }
+
+        let window = repo
+            .probe_window("database", "2026-05-10 00:00:00")
+            .await
+            .unwrap();
+        assert_eq!(
+            window,
+            ProbeWindow {
+                up: 2,
+                total: 3,
+                first_checked_at: Some("2026-05-10 10:00:00".to_string()),
+            }
+        );
+        assert_eq!(
+            repo.probe_window("api", "2026-05-10 00:00:00")
+                .await
+                .unwrap()
+                .total,
+            0
+        );
+    }
+
+    #[tokio::test]
+    async fn test_incident_lifecycle() {
+        let db = Database::in_memory().await.unwrap();
+        let repo = StatusRepository::new(db.pool().clone());
+
+        let incident = repo
+            .create_incident(
+                &CreateIncidentRequest {
+                    title: "LLM provider outage".to_string(),
+                    message: "Upstream errors".to_string(),
+                    severity: IncidentSeverity::Major,
+                    component: Some("llm_proxy".to_string()),
+                },
+                "admin",
+            )
+            .await
+            .unwrap();
+        assert_eq!(incident.severity, IncidentSeverity::Major);
+        assert!(incident.resolved_at.is_none());
+
+        let active = repo
+            .list_incidents(&IncidentListQuery::default())
+            .await
+            .unwrap();
+        assert_eq!(active.len(), 1);
+
+        let resolved = repo
+            .update_incident(
+                &incident.id,
+                &UpdateIncidentRequest {
+                    resolved: Some(true),
+                    ..Default::default()
+                },
+            )
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(resolved.resolved_at.is_some());
+        assert!(
+            repo.list_incidents(&IncidentListQuery::default())
+                .await
+                .unwrap()
+                .is_empty()
+        );
+        assert_eq!(
+            repo.list_incidents(&IncidentListQuery {
+                include_resolved: true,
+                limit: None,
+            })
+            .await
+            .unwrap()
+            .len(),
+            1
+        );
+
+        assert!(repo.delete_incident(&incident.id).await.unwrap());
+        assert!(!repo.delete_incident(&incident.id).await.unwrap());
+        assert!(
+            repo.update_incident("inc_missing", &UpdateIncidentRequest::default())
+                .await
+                .unwrap()
+                .is_none()
+        );
+    }
+}
@@ -0,0 +1,160 @@
+//! Local disk storage backend.
+
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use tokio::fs;
+
+use super::{StorageBackend, validate_key};
+
+/// Suffix of objects being written.
+const TMP_SUFFIX: &str = ".tmp-write";
+
+/// Objects stored as files under a root directory.
+#[derive(Debug, Clone)]
+pub struct LocalStorage {
+    root: PathBuf,
+}
+
+impl LocalStorage {
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self { root: root.into() }
+    }
+
+    fn path(&self, key: &str) -> Result<PathBuf> {
+        validate_key(key)?;
+        Ok(self.root.join(key))
+    }
+}
+
+#[async_trait]
+impl StorageBackend for LocalStorage {
+    async fn put(&self, key: &str, data: Vec<u8>, _content_type: &str) -> Result<()> {
+        let path = self.path(key)?;
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)
+                .await
+                .with_context(|| format!("creating {}", parent.display()))?;
+        }
+        // Write next to the target and rename so readers never see a
+        // partial object.
+        let mut tmp = path.clone().into_os_string();
+        tmp.push(TMP_SUFFIX);
+        let tmp = PathBuf::from(tmp);
+        fs::write(&tmp, data)
+            .await
+            .with_context(|| format!("writing {}", tmp.display()))?;
+        fs::rename(&tmp, &path)
+            .await
+            .with_context(|| format!("renaming {} -> {}", tmp.display(), path.display()))
+    }
+
+    async fn get(&self, key: &str) -> Result<Option<Vec<u8>>> {
+        let path = self.path(key)?;
+        match fs::read(&path).await {
+            Ok(data) => Ok(Some(data)),
+            Err(e) if e.kind() == ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e).with_context(|| format!("reading {}", path.display())),
+        }
+    }
+
+    async fn exists(&self, key: &str) -> Result<bool> {
+        let path = self.path(key)?;
+        fs::try_exists(&path)
+            .await
+            .with_context(|| format!("checking {}", path.display()))
+    }
+
+    async fn delete(&self, key: &str) -> Result<()> {
+        let path = self.path(key)?;
+        match fs::remove_file(&path).await {
+            Err(e) if e.kind() != ErrorKind::NotFound => {
+                Err(e).with_context(|| format!("removing {}", path.display()))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    async fn list(&self, prefix: &str) -> Result<Vec<String>> {
+        let mut keys = Vec::new();
+        let mut dirs = vec![self.root.clone()];
+        while let Some(dir) = dirs.pop() {
+            let mut entries = match fs::read_dir(&dir).await {
+                Ok(entries) => entries,
+                Err(e) if e.kind() == ErrorKind::NotFound => continue,
+                Err(e) => return Err(e).with_context(|| format!("reading {}", dir.display())),
+            };
+            while let Some(entry) = entries.next_entry().await? {
+                let path = entry.path();
+                if entry.file_type().await?.is_dir() {
+                    dirs.push(path);
+                } else if let Some(key) = relative_key(&self.root, &path)
+                    && key.starts_with(prefix)
+                    && !key.ends_with(TMP_SUFFIX)
+                {
+                    keys.push(key);
+                }
+            }
+        }
+        keys.sort();
+        Ok(keys)
+    }
+
+    fn describe(&self) -> String {
+        self.root.display().to_string()
+    }
+}
+
+fn relative_key(root: &Path, path: &Path) -> Option<String> {
+    let relative = path.strip_prefix(root).ok()?;
+    let parts: Option<Vec<&str>> = relative.iter().map(|part| part.to_str()).collect();
+    Some(parts?.join("/"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_local_roundtrip() {
+        let dir = tempfile::tempdir().unwrap();
+        let storage = LocalStorage::new(dir.path());
+
+        storage
+            .put("feedback/a.json", b"{}".to_vec(), "application/json")
+            .await
+            .unwrap();
+        storage
+            .put(
+                "crash-bundles/u1/b1.json",
+                b"[]".to_vec(),
+                "application/json",
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(
+            storage.get("feedback/a.json").await.unwrap().as_deref(),
+            Some(&b"{}"[..])
+        );
+        assert!(
+            storage
+                .get("feedback/missing.json")
+                .await
+                .unwrap()
+                .is_none()
+        );
+        assert!(storage.exists("crash-bundles/u1/b1.json").await.unwrap());
+        assert_eq!(
+            storage.list("crash-bundles/").await.unwrap(),
+            vec!["crash-bundles/u1/b1.json"]
+        );
+        assert!(storage.get("../outside").await.is_err());
+
+        storage.delete("feedback/a.json").await.unwrap();
+        storage.delete("feedback/a.json").await.unwrap();
+        assert!(!storage.exists("feedback/a.json").await.unwrap());
+    }
+}
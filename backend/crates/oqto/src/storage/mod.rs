@@ -0,0 +1,113 @@
+//! Object storage for files the backend keeps outside the database.
+//!
+//! Feedback archives and crash bundles are written through a
+//! [`StorageBackend`]: local disk by default, or an S3-compatible bucket
+//! (AWS S3, MinIO, ...) with `[storage] backend = "s3"` so every node of a
+//! multi-node deployment sees the same objects.
+//!
+//! Keys are `/`-separated relative paths such as
+//! `crash-bundles/<user_id>/<bundle_id>.json`.
+
+mod local;
+mod s3;
+
+pub use local::LocalStorage;
+pub use s3::{S3Config, S3Storage};
+
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use anyhow::{Context, Result, bail};
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+
+/// Storage backend selected in `[storage]`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum StorageKind {
+    #[default]
+    Local,
+    S3,
+}
+
+/// Object storage configuration.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct StorageConfig {
+    pub backend: StorageKind,
+    /// Root directory of the local backend. Empty uses `<data_dir>/storage`.
+    pub local_dir: String,
+    pub s3: S3Config,
+}
+
+/// Flat key/value object store.
+#[async_trait]
+pub trait StorageBackend: Send + Sync {
+    /// Store `data` under `key`, replacing any existing object.
+    async fn put(&self, key: &str, data: Vec<u8>, content_type: &str) -> Result<()>;
+
+    /// Read an object; None when it does not exist.
+    async fn get(&self, key: &str) -> Result<Option<Vec<u8>>>;
+
+    /// Whether an object exists.
+    async fn exists(&self, key: &str) -> Result<bool>;
+
+    /// Delete an object. Deleting a missing object is not an error.
+    async fn delete(&self, key: &str) -> Result<()>;
+
+    /// Keys starting with `prefix`, sorted.
+    async fn list(&self, prefix: &str) -> Result<Vec<String>>;
+
+    /// Short description for logs (e.g. `s3://bucket/prefix`).
+    fn describe(&self) -> String;
+}
+
+/// Build the configured backend.
+pub fn from_config(config: &StorageConfig, data_dir: &Path) -> Result<Arc<dyn StorageBackend>> {
+    Ok(match config.backend {
+        StorageKind::Local => {
+            let root = if config.local_dir.is_empty() {
+                data_dir.join("storage")
+            } else {
+                PathBuf::from(
+                    shellexpand::full(&config.local_dir)
+                        .context("expanding [storage] local_dir")?
+                        .into_owned(),
+                )
+            };
+            Arc::new(LocalStorage::new(root))
+        }
+        StorageKind::S3 => Arc::new(S3Storage::new(config.s3.clone())?),
+    })
+}
+
+/// Reject keys that are empty, absolute, or escape their prefix.
+pub fn validate_key(key: &str) -> Result<()> {
+    if key.is_empty() || key.len() > 1024 {
+        bail!("storage key must be 1-1024 bytes");
+    }
+    if key.starts_with('/') || key.contains('\\') || key.contains('\0') {
+        bail!("invalid storage key: {key}");
+    }
+    if key
+        .split('/')
+        .any(|segment| segment.is_empty() || segment == "." || segment == "..")
+    {
+        bail!("invalid storage key: {key}");
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validate_key() {
+        assert!(validate_key("feedback/2026-01-01_abc.json").is_ok());
+        assert!(validate_key("crash-bundles/u1/b1.json").is_ok());
+        for key in ["", "/abs", "a/../b", "a//b", "a\\b", "trailing/"] {
+            assert!(validate_key(key).is_err(), "{key}");
+        }
+    }
+}
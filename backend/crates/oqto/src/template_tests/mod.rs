@@ -0,0 +1,153 @@
+//! Smoke-check runs of workspace templates.
+//!
+//! A template declares a check in its `template.json`:
+//!
+//! ```json
+//! { "smoke_check": { "command": "npm ci && npm test", "timeout_secs": 600 } }
+//! ```
+//!
+//! Testing a template creates a throwaway project from it in the tester's
+//! workspace, has the runner run the command there, records the result and
+//! deletes the project again. Results are kept per template version, a hash
+//! of the template's files, so a result can be told apart from one of an
+//! older revision of the same template.
+
+mod models;
+mod repository;
+
+pub use models::{SmokeCheck, TemplateTestListQuery, TemplateTestRun, TemplateTestStatus};
+pub use repository::{NewTemplateTestRun, TemplateTestRepository};
+
+use std::path::Path;
+
+use anyhow::{Context, Result};
+use sha2::{Digest, Sha256};
+
+/// Workspace directory the throwaway projects are created in.
+pub const SANDBOX_DIR: &str = ".oqto-template-tests";
+
+/// The template's declared smoke check; None when it declares none.
+pub fn read_smoke_check(template_dir: &Path) -> Result<Option<SmokeCheck>> {
+    let path = template_dir.join("template.json");
+    let contents = match std::fs::read_to_string(&path) {
+        Ok(contents) => contents,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(e).with_context(|| format!("reading {}", path.display())),
+    };
+    let value: serde_json::Value =
+        serde_json::from_str(&contents).with_context(|| format!("parsing {}", path.display()))?;
+    let Some(check) = value.get("smoke_check") else {
+        return Ok(None);
+    };
+    let check: SmokeCheck = serde_json::from_value(check.clone())
+        .with_context(|| format!("invalid smoke_check in {}", path.display()))?;
+    Ok((!check.command.trim().is_empty()).then_some(check))
+}
+
+/// Version of a template: a hash over the relative paths and contents of
+/// its files, `.git` excluded. The same files give the same version
+/// wherever the template is checked out.
+pub fn template_version(template_dir: &Path) -> Result<String> {
+    let mut files = Vec::new();
+    collect_files(template_dir, template_dir, &mut files)?;
+    files.sort();
+    let mut hasher = Sha256::new();
+    for rel in &files {
+        let contents = std::fs::read(template_dir.join(rel))
+            .with_context(|| format!("reading template file {rel}"))?;
+        hasher.update(rel.as_bytes());
+        hasher.update([0]);
+        hasher.update((contents.len() as u64).to_le_bytes());
+        hasher.update(&contents);
+    }
+    Ok(hex::encode(&hasher.finalize()[..8]))
+}
+
+fn collect_files(root: &Path, dir: &Path, files: &mut Vec<String>) -> Result<()> {
+    for entry in std::fs::read_dir(dir).with_context(|| format!("reading {}", dir.display()))? {
+        let entry = entry?;
+        if entry.file_name() == ".git" {
+            continue;
+        }
+        let path = entry.path();
+        let file_type = entry.file_type()?;
+        if file_type.is_dir() {
+            collect_files(root, &path, files)?;
+        } else if file_type.is_file() {
+            let rel = path.strip_prefix(root).unwrap_or(&path);
+            files.push(rel.to_string_lossy().replace('\\', "/"));
+        }
+    }
+    Ok(())
+}
+
+/// Last commit of the templates repository touching `template`, when the
+/// repository is a git checkout.
+pub async fn template_commit(repo_path: &Path, template: &str) -> Option<String> {
+    let output = tokio::process::Command::new("git")
+        .arg("-C")
+        .arg(repo_path)
+        .args(["log", "-1", "--format=%H", "--", template])
+        .output()
+        .await
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    let commit = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    (!commit.is_empty()).then_some(commit)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn smoke_checks_are_read_from_template_json() {
+        let dir = tempfile::tempdir().unwrap();
+        assert_eq!(read_smoke_check(dir.path()).unwrap(), None);
+
+        std::fs::write(
+            dir.path().join("template.json"),
+            r#"{"description": "CLI", "smoke_check": {"command": "cargo test", "timeout_secs": 600}}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            read_smoke_check(dir.path()).unwrap(),
+            Some(SmokeCheck {
+                command: "cargo test".to_string(),
+                timeout_secs: Some(600),
+            })
+        );
+
+        std::fs::write(
+            dir.path().join("template.json"),
+            r#"{"smoke_check": {"command": "  "}}"#,
+        )
+        .unwrap();
+        assert_eq!(read_smoke_check(dir.path()).unwrap(), None);
+
+        std::fs::write(dir.path().join("template.json"), r#"{"smoke_check": 1}"#).unwrap();
+        assert!(read_smoke_check(dir.path()).is_err());
+    }
+
+    #[test]
+    fn versions_follow_file_contents_only() {
+        let a = tempfile::tempdir().unwrap();
+        let b = tempfile::tempdir().unwrap();
+        for dir in [a.path(), b.path()] {
+            std::fs::create_dir_all(dir.join("src")).unwrap();
+            std::fs::write(dir.join("src/main.rs"), "fn main() {}").unwrap();
+            std::fs::write(dir.join("template.json"), "{}").unwrap();
+        }
+        std::fs::create_dir_all(b.path().join(".git")).unwrap();
+        std::fs::write(b.path().join(".git/HEAD"), "ref: main").unwrap();
+
+        let version = template_version(a.path()).unwrap();
+        assert_eq!(version.len(), 16);
+        assert_eq!(version, template_version(b.path()).unwrap());
+
+        std::fs::write(b.path().join("src/main.rs"), "fn main() { }").unwrap();
+        assert_ne!(version, template_version(b.path()).unwrap());
+    }
+}
@@ -0,0 +1,77 @@
+use serde::{Deserialize, Serialize};
+
+/// Outcome of a template test run.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TemplateTestStatus {
+    /// The check exited with 0.
+    Passed,
+    /// The check exited with another code or was killed by a signal.
+    Failed,
+    /// The check ran out of time and was killed.
+    TimedOut,
+    /// The check could not be run: the throwaway project could not be
+    /// created or the runner failed. `output` says why.
+    Error,
+}
+
+impl TemplateTestStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TemplateTestStatus::Passed => "passed",
+            TemplateTestStatus::Failed => "failed",
+            TemplateTestStatus::TimedOut => "timed_out",
+            TemplateTestStatus::Error => "error",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "passed" => Some(TemplateTestStatus::Passed),
+            "failed" => Some(TemplateTestStatus::Failed),
+            "timed_out" => Some(TemplateTestStatus::TimedOut),
+            "error" => Some(TemplateTestStatus::Error),
+            _ => None,
+        }
+    }
+}
+
+/// The `smoke_check` entry of a template's `template.json`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SmokeCheck {
+    /// Shell command run in the root of a project created from the template.
+    pub command: String,
+    /// Time limit; the runner's default (300s) when unset.
+    #[serde(default)]
+    pub timeout_secs: Option<u64>,
+}
+
+/// One smoke check of one template version.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct TemplateTestRun {
+    pub id: String,
+    pub template: String,
+    /// Hash of the template's files when it was tested.
+    pub version: String,
+    /// Last commit of the templates repository touching the template, when
+    /// it is a git checkout.
+    pub commit: Option<String>,
+    pub command: String,
+    pub status: TemplateTestStatus,
+    pub exit_code: Option<i64>,
+    /// Tail of the merged stdout and stderr, or the error.
+    pub output: String,
+    pub output_truncated: bool,
+    pub duration_ms: i64,
+    /// Who ran the test.
+    pub started_by: Option<String>,
+    pub created_at: String,
+}
+
+/// Query of `GET /templates/{name}/tests`.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct TemplateTestListQuery {
+    /// Only runs of this version; `current` for the template as it is now.
+    pub version: Option<String>,
+    pub limit: Option<i64>,
+}
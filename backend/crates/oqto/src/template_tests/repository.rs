@@ -0,0 +1,204 @@
+use anyhow::{Context, Result};
+use sqlx::FromRow;
+
+use crate::db::{self, DbPool, on_pool};
+
+use super::{TemplateTestRun, TemplateTestStatus};
+
+const RUN_COLUMNS: &str = "id, template, version, git_commit, command, status, exit_code, \
+     output, output_truncated, duration_ms, started_by, created_at";
+
+/// Runs kept per template; older ones are dropped as new ones come in.
+const RUNS_KEPT: i64 = 100;
+
+/// Most runs in one list.
+const MAX_LIST: i64 = 100;
+
+#[derive(Debug, Clone, FromRow)]
+struct RunRow {
+    id: String,
+    template: String,
+    version: String,
+    git_commit: Option<String>,
+    command: String,
+    status: String,
+    exit_code: Option<i64>,
+    output: String,
+    output_truncated: bool,
+    duration_ms: i64,
+    started_by: Option<String>,
+    created_at: String,
+}
+
+impl From<RunRow> for TemplateTestRun {
+    fn from(row: RunRow) -> Self {
+        Self {
+            id: row.id,
+            template: row.template,
+            version: row.version,
+            commit: row.git_commit,
+            command: row.command,
+            // The column is constrained to the known values.
+            status: TemplateTestStatus::parse(&row.status).unwrap_or(TemplateTestStatus::Error),
+            exit_code: row.exit_code,
+            output: row.output,
+            output_truncated: row.output_truncated,
+            duration_ms: row.duration_ms,
+            started_by: row.started_by,
+            created_at: row.created_at,
+        }
+    }
+}
+
+/// A finished test run to record.
+#[derive(Debug, Clone)]
+pub struct NewTemplateTestRun<'a> {
+    pub template: &'a str,
+    pub version: &'a str,
+    pub commit: Option<&'a str>,
+    pub command: &'a str,
+    pub status: TemplateTestStatus,
+    pub exit_code: Option<i64>,
+    pub output: &'a str,
+    pub output_truncated: bool,
+    pub duration_ms: i64,
+    pub started_by: &'a str,
+}
+
+#[derive(Debug, Clone)]
+pub struct TemplateTestRepository {
+    pool: DbPool,
+}
+
+impl TemplateTestRepository {
+    pub fn new(pool: DbPool) -> Self {
+        Self { pool }
+    }
+
+    /// Record a run and drop the template's oldest runs beyond the ones
+    /// kept.
+    pub async fn create(&self, run: NewTemplateTestRun<'_>) -> Result<TemplateTestRun> {
+        let id = format!("ttr_{}", nanoid::nanoid!());
+        let now = db::now();
+        on_pool!(&self.pool, |pool| sqlx::query(
+            "INSERT INTO template_test_runs (id, template, version, git_commit, command, status, \
+             exit_code, output, output_truncated, duration_ms, started_by, created_at) \
+             VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12)"
+        )
+        .bind(&id)
+        .bind(run.template)
+        .bind(run.version)
+        .bind(run.commit)
+        .bind(run.command)
+        .bind(run.status.as_str())
+        .bind(run.exit_code)
+        .bind(run.output)
+        .bind(run.output_truncated)
+        .bind(run.duration_ms)
+        .bind(run.started_by)
+        .bind(&now)
+        .execute(pool)
+        .await
+        .map(|_| ()))
+        .context("insert template test run")?;
+
+        on_pool!(&self.pool, |pool| sqlx::query(
+            "DELETE FROM template_test_runs WHERE template = $1 AND id NOT IN \
+             (SELECT id FROM template_test_runs WHERE template = $1 \
+              ORDER BY created_at DESC, id DESC LIMIT $2)"
+        )
+        .bind(run.template)
+        .bind(RUNS_KEPT)
+        .execute(pool)
+        .await
+        .map(|_| ()))
+        .context("prune template test runs")?;
+
+        Ok(TemplateTestRun {
+            id,
+            template: run.template.to_string(),
+            version: run.version.to_string(),
+            commit: run.commit.map(str::to_string),
+            command: run.command.to_string(),
+            status: run.status,
+            exit_code: run.exit_code,
+            output: run.output.to_string(),
+            output_truncated: run.output_truncated,
+            duration_ms: run.duration_ms,
+            started_by: Some(run.started_by.to_string()),
+            created_at: now,
+        })
+    }
+
+    /// Runs of a template, newest first, optionally of one version only.
+    pub async fn list(
+        &self,
+        template: &str,
+        version: Option<&str>,
+        limit: Option<i64>,
+    ) -> Result<Vec<TemplateTestRun>> {
+        let limit = limit.unwrap_or(20).clamp(1, MAX_LIST);
+        let sql = format!(
+            "SELECT {RUN_COLUMNS} FROM template_test_runs \
+             WHERE template = $1 AND ($2 IS NULL OR version = $2) \
+             ORDER BY created_at DESC, id DESC LIMIT $3"
+        );
+        let rows: Vec<RunRow> = on_pool!(&self.pool, |pool| sqlx::query_as(&sql)
+            .bind(template)
+            .bind(version)
+            .bind(limit)
+            .fetch_all(pool)
+            .await)
+        .context("list template test runs")?;
+        Ok(rows.into_iter().map(TemplateTestRun::from).collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::Database;
+
+    fn run<'a>(version: &'a str, status: TemplateTestStatus) -> NewTemplateTestRun<'a> {
+        NewTemplateTestRun {
+            template: "rust-cli",
+            version,
+            commit: None,
+            command: "cargo test",
+            status,
+            exit_code: Some(0),
+            output: "ok",
+            output_truncated: false,
+            duration_ms: 1200,
+            started_by: "admin",
+        }
+    }
+
+    #[tokio::test]
+    async fn runs_are_listed_per_template_and_version() {
+        let db = Database::in_memory().await.unwrap();
+        sqlx::query("INSERT INTO users (id, username, email, display_name) VALUES (?, ?, ?, ?)")
+            .bind("admin")
+            .bind("admin")
+            .bind("admin@example.com")
+            .bind("admin")
+            .execute(db.pool())
+            .await
+            .unwrap();
+        let repo = TemplateTestRepository::new(db.shared().clone());
+
+        let first = repo
+            .create(run("aaaa", TemplateTestStatus::Failed))
+            .await
+            .unwrap();
+        repo.create(run("bbbb", TemplateTestStatus::Passed))
+            .await
+            .unwrap();
+
+        let all = repo.list("rust-cli", None, None).await.unwrap();
+        assert_eq!(all.len(), 2);
+        let old = repo.list("rust-cli", Some("aaaa"), None).await.unwrap();
+        assert_eq!(old, vec![first]);
+        assert!(repo.list("python", None, None).await.unwrap().is_empty());
+    }
+}
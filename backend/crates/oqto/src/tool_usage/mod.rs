@@ -0,0 +1,254 @@
+//! Agent tool usage statistics.
+//!
+//! Observes canonical `tool.start` / `tool.end` events on the agent stream,
+//! aggregates them into daily rollups per user, agent harness, project and
+//! tool, and raises a notification when a tool keeps failing.
+
+mod models;
+mod repository;
+
+pub use models::{
+    ToolCallRecord, ToolStatsGroupBy, ToolStatsQuery, ToolStatsSort, ToolUsageConfig, ToolUsageStat,
+};
+pub use repository::ToolUsageRepository;
+
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use chrono::{DateTime, Utc};
+use dashmap::DashMap;
+use dashmap::mapref::entry::Entry;
+use oqto_protocol::events::{Event, EventPayload};
+use tracing::{debug, warn};
+
+use crate::ws::{WsEvent, WsHub};
+
+/// Upper bound on tracked in-flight tool calls before stale entries are pruned.
+const MAX_INFLIGHT: usize = 10_000;
+/// In-flight entries older than this are dropped when pruning.
+const INFLIGHT_STALE_MS: i64 = 3_600_000;
+/// How often the dedup log is pruned.
+const MAINTENANCE_INTERVAL: Duration = Duration::from_secs(3600);
+
+/// Who and where a tool call happened, resolved by the event forwarder.
+#[derive(Debug, Clone)]
+pub struct ToolUsageContext {
+    pub user_id: String,
+    pub agent: String,
+    pub project: String,
+}
+
+/// Tool usage tracking service.
+pub struct ToolUsageService {
+    repo: ToolUsageRepository,
+    config: ToolUsageConfig,
+    hub: Arc<WsHub>,
+    /// Start timestamps (ms) of in-flight tool calls keyed by session/tool call.
+    inflight: DashMap<String, i64>,
+    /// Last anomaly notification per user/tool.
+    last_alert: DashMap<String, Instant>,
+}
+
+impl ToolUsageService {
+    pub fn new(repo: ToolUsageRepository, config: ToolUsageConfig, hub: Arc<WsHub>) -> Self {
+        Self {
+            repo,
+            config,
+            hub,
+            inflight: DashMap::new(),
+            last_alert: DashMap::new(),
+        }
+    }
+
+    pub fn repository(&self) -> &ToolUsageRepository {
+        &self.repo
+    }
+
+    /// Observe a canonical agent event. Non-tool events are ignored.
+    ///
+    /// Persistence happens on a background task so the event stream is never
+    /// blocked on the database.
+    pub fn observe(self: &Arc<Self>, ctx: &ToolUsageContext, event: &Event) {
+        match &event.payload {
+            EventPayload::ToolStart { tool_call_id, .. } => {
+                if self.inflight.len() >= MAX_INFLIGHT {
+                    self.inflight
+                        .retain(|_, started| event.ts - *started < INFLIGHT_STALE_MS);
+                }
+                self.inflight
+                    .insert(inflight_key(&event.session_id, tool_call_id), event.ts);
+            }
+            EventPayload::ToolEnd {
+                tool_call_id,
+                name,
+                is_error,
+                duration_ms,
+                ..
+            } => {
+                let started = self
+                    .inflight
+                    .remove(&inflight_key(&event.session_id, tool_call_id))
+                    .map(|(_, ts)| ts);
+                let duration_ms =
+                    duration_ms.or_else(|| started.and_then(|s| u64::try_from(event.ts - s).ok()));
+
+                let record = ToolCallRecord {
+                    session_id: event.session_id.clone(),
+                    tool_call_id: tool_call_id.clone(),
+                    user_id: ctx.user_id.clone(),
+                    agent: ctx.agent.clone(),
+                    project: ctx.project.clone(),
+                    tool_name: name.clone(),
+                    is_error: *is_error,
+                    duration_ms,
+                    day: day_for_ts(event.ts),
+                };
+                let service = Arc::clone(self);
+                tokio::spawn(async move {
+                    service.record(record).await;
+                });
+            }
+            _ => {}
+        }
+    }
+
+    async fn record(&self, record: ToolCallRecord) {
+        match self.repo.record_call(&record).await {
+            Ok(true) if record.is_error => self.check_anomaly(&record).await,
+            Ok(_) => {}
+            Err(e) => warn!(
+                tool = %record.tool_name,
+                session_id = %record.session_id,
+                "Failed to record tool usage: {e:#}"
+            ),
+        }
+    }
+
+    /// Notify the user when a tool's failure rate over the current and
+    /// previous UTC day crosses the configured threshold.
+    async fn check_anomaly(&self, record: &ToolCallRecord) {
+        let since = day_for_ts((Utc::now() - chrono::Duration::days(1)).timestamp_millis());
+        let (calls, failures) = match self
+            .repo
+            .failure_window(&record.user_id, &record.tool_name, &since)
+            .await
+        {
+            Ok(window) => window,
+            Err(e) => {
+                warn!("Failed to query tool failure window: {e:#}");
+                return;
+            }
+        };
+
+        if !is_anomalous(calls, failures, &self.config) {
+            return;
+        }
+
+        let cooldown = Duration::from_secs(self.config.anomaly_cooldown_minutes * 60);
+        let now = Instant::now();
+        match self
+            .last_alert
+            .entry(format!("{}\u{0}{}", record.user_id, record.tool_name))
+        {
+            Entry::Occupied(mut entry) => {
+                if now.duration_since(*entry.get()) < cooldown {
+                    return;
+                }
+                entry.insert(now);
+            }
+            Entry::Vacant(entry) => {
+                entry.insert(now);
+            }
+        }
+
+        let failure_rate = failures as f64 / calls as f64;
+        warn!(
+            user_id = %record.user_id,
+            tool = %record.tool_name,
+            calls,
+            failures,
+            "Tool failure rate anomaly"
+        );
+        self.hub
+            .send_to_user(
+                &record.user_id,
+                WsEvent::Notification {
+                    level: "warning".to_string(),
+                    title: format!("Tool `{}` keeps failing", record.tool_name),
+                    message: format!(
+                        "{failures} of the last {calls} calls failed ({:.0}%). \
+                         Check the agent configuration for this tool.",
+                        failure_rate * 100.0
+                    ),
+                    category: "tool_usage.anomaly".to_string(),
+                    detail: Some(serde_json::json!({
+                        "tool_name": record.tool_name,
+                        "agent": record.agent,
+                        "project": record.project,
+                        "calls": calls,
+                        "failures": failures,
+                        "failure_rate": failure_rate,
+                    })),
+                },
+            )
+            .await;
+    }
+
+    /// Periodically prune the per-call dedup log.
+    pub fn start_maintenance_task(self: &Arc<Self>) -> tokio::task::JoinHandle<()> {
+        let service = Arc::clone(self);
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(MAINTENANCE_INTERVAL);
+            loop {
+                interval.tick().await;
+                match service
+                    .repo
+                    .prune_call_log(service.config.call_log_retention_days)
+                    .await
+                {
+                    Ok(0) => {}
+                    Ok(n) => debug!("Pruned {n} tool call log entries"),
+                    Err(e) => warn!("Failed to prune tool call log: {e:#}"),
+                }
+            }
+        })
+    }
+}
+
+fn inflight_key(session_id: &str, tool_call_id: &str) -> String {
+    format!("{session_id}\u{0}{tool_call_id}")
+}
+
+fn day_for_ts(ts_ms: i64) -> String {
+    DateTime::from_timestamp_millis(ts_ms)
+        .unwrap_or_else(Utc::now)
+        .format("%Y-%m-%d")
+        .to_string()
+}
+
+fn is_anomalous(calls: i64, failures: i64, config: &ToolUsageConfig) -> bool {
+    calls > 0
+        && calls >= config.anomaly_min_calls
+        && failures as f64 / calls as f64 >= config.anomaly_failure_rate
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_anomalous_thresholds() {
+        let config = ToolUsageConfig::default();
+        assert!(!is_anomalous(0, 0, &config));
+        // Below the minimum sample size.
+        assert!(!is_anomalous(5, 5, &config));
+        assert!(!is_anomalous(10, 7, &config));
+        assert!(is_anomalous(10, 8, &config));
+        assert!(is_anomalous(20, 20, &config));
+    }
+
+    #[test]
+    fn test_day_for_ts() {
+        assert_eq!(day_for_ts(1_738_764_000_000), "2025-02-05");
+    }
+}
@@ -0,0 +1,122 @@
+use serde::{Deserialize, Serialize};
+
+/// Tool usage tracking configuration.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ToolUsageConfig {
+    /// Record tool-call statistics from agent events.
+    pub enabled: bool,
+    /// Failure rate (0.0-1.0) at which a tool is reported as anomalous.
+    pub anomaly_failure_rate: f64,
+    /// Minimum number of calls in the window before anomalies are reported.
+    pub anomaly_min_calls: i64,
+    /// Minimum time between two anomaly notifications for the same user and tool.
+    pub anomaly_cooldown_minutes: u64,
+    /// How long per-call dedup entries are kept.
+    pub call_log_retention_days: i64,
+}
+
+impl Default for ToolUsageConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            anomaly_failure_rate: 0.8,
+            anomaly_min_calls: 10,
+            anomaly_cooldown_minutes: 360,
+            call_log_retention_days: 7,
+        }
+    }
+}
+
+/// A completed tool call observed on the agent event stream.
+#[derive(Debug, Clone)]
+pub struct ToolCallRecord {
+    pub session_id: String,
+    pub tool_call_id: String,
+    pub user_id: String,
+    /// Agent harness (e.g. "pi").
+    pub agent: String,
+    /// Project the session runs in (workspace path).
+    pub project: String,
+    pub tool_name: String,
+    pub is_error: bool,
+    pub duration_ms: Option<u64>,
+    /// UTC day (YYYY-MM-DD) the call finished on.
+    pub day: String,
+}
+
+/// Dimension to aggregate tool statistics by.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ToolStatsGroupBy {
+    #[default]
+    Tool,
+    Agent,
+    Project,
+    User,
+}
+
+impl ToolStatsGroupBy {
+    pub(super) fn column(self) -> &'static str {
+        match self {
+            Self::Tool => "tool_name",
+            Self::Agent => "agent",
+            Self::Project => "project",
+            Self::User => "user_id",
+        }
+    }
+}
+
+/// Ordering for leaderboards.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ToolStatsSort {
+    #[default]
+    Calls,
+    Failures,
+    FailureRate,
+    AvgDuration,
+}
+
+impl ToolStatsSort {
+    pub(super) fn order_by(self) -> &'static str {
+        match self {
+            Self::Calls => "calls DESC",
+            Self::Failures => "failures DESC, calls DESC",
+            Self::FailureRate => "failure_rate DESC, calls DESC",
+            Self::AvgDuration => "avg_duration_ms DESC",
+        }
+    }
+}
+
+/// Query parameters for tool statistics.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct ToolStatsQuery {
+    #[serde(default)]
+    pub group_by: ToolStatsGroupBy,
+    #[serde(default)]
+    pub sort: ToolStatsSort,
+    /// First day to include (YYYY-MM-DD, inclusive).
+    pub since: Option<String>,
+    /// Last day to include (YYYY-MM-DD, inclusive).
+    pub until: Option<String>,
+    pub agent: Option<String>,
+    pub project: Option<String>,
+    pub tool: Option<String>,
+    /// Restrict to one user (admin endpoint only).
+    pub user_id: Option<String>,
+    pub limit: Option<i64>,
+}
+
+/// One row of aggregated tool statistics.
+#[derive(Debug, Clone, Serialize)]
+pub struct ToolUsageStat {
+    /// Value of the grouping dimension (tool name, agent, project or user ID).
+    pub key: String,
+    pub calls: i64,
+    pub failures: i64,
+    pub failure_rate: f64,
+    /// Average duration of calls that reported one.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub avg_duration_ms: Option<f64>,
+}
@@ -0,0 +1,268 @@
+use anyhow::{Context, Result};
+use sqlx::{FromRow, QueryBuilder, Sqlite, SqlitePool};
+
+use super::{ToolCallRecord, ToolStatsQuery, ToolUsageStat};
+
+const DEFAULT_STATS_LIMIT: i64 = 50;
+const MAX_STATS_LIMIT: i64 = 500;
+
+#[derive(Debug, Clone, FromRow)]
+struct ToolUsageStatRow {
+    group_key: String,
+    calls: i64,
+    failures: i64,
+    failure_rate: f64,
+    avg_duration_ms: Option<f64>,
+}
+
+#[derive(Debug, Clone)]
+pub struct ToolUsageRepository {
+    pool: SqlitePool,
+}
+
+impl ToolUsageRepository {
+    pub fn new(pool: SqlitePool) -> Self {
+        Self { pool }
+    }
+
+    /// Record a completed tool call into the daily rollup.
+    ///
+    /// Returns `false` if the call was already recorded (duplicate observation).
+    pub async fn record_call(&self, record: &ToolCallRecord) -> Result<bool> {
+        let mut tx = self
+            .pool
+            .begin()
+            .await
+            .context("begin tool usage transaction")?;
+
+        let inserted = sqlx::query(
+            r#"INSERT OR IGNORE INTO tool_call_log (session_id, tool_call_id) VALUES (?, ?)"#,
+        )
+        .bind(&record.session_id)
+        .bind(&record.tool_call_id)
+        .execute(&mut *tx)
+        .await
+        .context("insert tool call log entry")?
+        .rows_affected()
+            == 1;
+
+        if !inserted {
+            return Ok(false);
+        }
+
+        let timed_calls = i64::from(record.duration_ms.is_some());
+        let duration_ms = record
+            .duration_ms
+            .map(|ms| i64::try_from(ms).unwrap_or(i64::MAX))
+            .unwrap_or(0);
+
+        sqlx::query(
+            r#"
+            INSERT INTO tool_usage_daily
+                (day, user_id, agent, project, tool_name, calls, failures, timed_calls, total_duration_ms)
+            VALUES (?, ?, ?, ?, ?, 1, ?, ?, ?)
+            ON CONFLICT(day, user_id, agent, project, tool_name) DO UPDATE SET
+                calls = calls + 1,
+                failures = failures + excluded.failures,
+                timed_calls = timed_calls + excluded.timed_calls,
+                total_duration_ms = total_duration_ms + excluded.total_duration_ms,
+                updated_at = datetime('now')
+            "#,
+        )
+        .bind(&record.day)
+        .bind(&record.user_id)
+        .bind(&record.agent)
+        .bind(&record.project)
+        .bind(&record.tool_name)
+        .bind(i64::from(record.is_error))
+        .bind(timed_calls)
+        .bind(duration_ms)
+        .execute(&mut *tx)
+        .await
+        .context("upsert tool usage rollup")?;
+
+        tx.commit().await.context("commit tool usage transaction")?;
+        Ok(true)
+    }
+
+    /// Aggregate rollups by the requested dimension.
+    ///
+    /// `user_id` restricts results to a single user; `query.user_id` is ignored
+    /// when it is set (callers decide which one applies).
+    pub async fn stats(
+        &self,
+        query: &ToolStatsQuery,
+        user_id: Option<&str>,
+    ) -> Result<Vec<ToolUsageStat>> {
+        let column = query.group_by.column();
+        let mut qb = QueryBuilder::<Sqlite>::new(format!(
+            r#"SELECT {column} AS group_key,
+                   SUM(calls) AS calls,
+                   SUM(failures) AS failures,
+                   CAST(SUM(failures) AS REAL) / MAX(SUM(calls), 1) AS failure_rate,
+                   CASE WHEN SUM(timed_calls) > 0
+                        THEN CAST(SUM(total_duration_ms) AS REAL) / SUM(timed_calls)
+                   END AS avg_duration_ms
+               FROM tool_usage_daily WHERE 1 = 1"#
+        ));
+
+        if let Some(user_id) = user_id.or(query.user_id.as_deref()) {
+            qb.push(" AND user_id = ").push_bind(user_id.to_string());
+        }
+        if let Some(since) = query.since.as_deref() {
+            qb.push(" AND day >= ").push_bind(since.to_string());
+        }
+        if let Some(until) = query.until.as_deref() {
+            qb.push(" AND day <= ").push_bind(until.to_string());
+        }
+        if let Some(agent) = query.agent.as_deref() {
+            qb.push(" AND agent = ").push_bind(agent.to_string());
+        }
+        if let Some(project) = query.project.as_deref() {
+            qb.push(" AND project = ").push_bind(project.to_string());
+        }
+        if let Some(tool) = query.tool.as_deref() {
+            qb.push(" AND tool_name = ").push_bind(tool.to_string());
+        }
+
+        let limit = query
+            .limit
+            .unwrap_or(DEFAULT_STATS_LIMIT)
+            .clamp(1, MAX_STATS_LIMIT);
+        qb.push(format!(
+            " GROUP BY {column} ORDER BY {} LIMIT ",
+            query.sort.order_by()
+        ))
+        .push_bind(limit);
+
+        let rows = qb
+            .build_query_as::<ToolUsageStatRow>()
+            .fetch_all(&self.pool)
+            .await
+            .context("query tool usage stats")?;
+
+        Ok(rows
+            .into_iter()
+            .map(|row| ToolUsageStat {
+                key: row.group_key,
+                calls: row.calls,
+                failures: row.failures,
+                failure_rate: row.failure_rate,
+                avg_duration_ms: row.avg_duration_ms,
+            })
+            .collect())
+    }
+
+    /// Total calls and failures for one user's tool since `since_day` (inclusive).
+    pub async fn failure_window(
+        &self,
+        user_id: &str,
+        tool_name: &str,
+        since_day: &str,
+    ) -> Result<(i64, i64)> {
+        sqlx::query_as::<_, (i64, i64)>(
+            r#"SELECT COALESCE(SUM(calls), 0), COALESCE(SUM(failures), 0)
+               FROM tool_usage_daily WHERE user_id = ? AND tool_name = ? AND day >= ?"#,
+        )
+        .bind(user_id)
+        .bind(tool_name)
+        .bind(since_day)
+        .fetch_one(&self.pool)
+        .await
+        .context("query tool failure window")
+    }
+
+    /// Drop dedup entries older than `retention_days`.
+    pub async fn prune_call_log(&self, retention_days: i64) -> Result<u64> {
+        let result =
+            sqlx::query(r#"DELETE FROM tool_call_log WHERE recorded_at < datetime('now', ?)"#)
+                .bind(format!("-{retention_days} days"))
+                .execute(&self.pool)
+                .await
+                .context("prune tool call log")?;
+        Ok(result.rows_affected())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::Database;
+    use crate::tool_usage::{ToolStatsGroupBy, ToolStatsSort};
+
+    fn record(tool_call_id: &str, tool_name: &str, is_error: bool) -> ToolCallRecord {
+        ToolCallRecord {
+            session_id: "ses_1".to_string(),
+            tool_call_id: tool_call_id.to_string(),
+            user_id: "alice".to_string(),
+            agent: "pi".to_string(),
+            project: "/home/alice/proj".to_string(),
+            tool_name: tool_name.to_string(),
+            is_error,
+            duration_ms: Some(100),
+            day: "2026-05-09".to_string(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_record_call_dedups_and_aggregates() {
+        let db = Database::in_memory().await.unwrap();
+        let repo = ToolUsageRepository::new(db.pool().clone());
+
+        assert!(
+            repo.record_call(&record("c1", "bash", false))
+                .await
+                .unwrap()
+        );
+        assert!(
+            !repo
+                .record_call(&record("c1", "bash", false))
+                .await
+                .unwrap()
+        );
+        assert!(repo.record_call(&record("c2", "bash", true)).await.unwrap());
+        assert!(
+            repo.record_call(&record("c3", "read", false))
+                .await
+                .unwrap()
+        );
+
+        let stats = repo
+            .stats(&ToolStatsQuery::default(), Some("alice"))
+            .await
+            .unwrap();
+        assert_eq!(stats.len(), 2);
+        assert_eq!(stats[0].key, "bash");
+        assert_eq!(stats[0].calls, 2);
+        assert_eq!(stats[0].failures, 1);
+        assert!((stats[0].failure_rate - 0.5).abs() < f64::EPSILON);
+        assert_eq!(stats[0].avg_duration_ms, Some(100.0));
+
+        let by_agent = repo
+            .stats(
+                &ToolStatsQuery {
+                    group_by: ToolStatsGroupBy::Agent,
+                    sort: ToolStatsSort::FailureRate,
+                    ..Default::default()
+                },
+                None,
+            )
+            .await
+            .unwrap();
+        assert_eq!(by_agent.len(), 1);
+        assert_eq!(by_agent[0].calls, 3);
+
+        assert_eq!(
+            repo.failure_window("alice", "bash", "2026-05-08")
+                .await
+                .unwrap(),
+            (2, 1)
+        );
+        assert!(
+            repo.stats(&ToolStatsQuery::default(), Some("bob"))
+                .await
+                .unwrap()
+                .is_empty()
+        );
+    }
+}
@@ -0,0 +1,265 @@
+//! Minimal IMAP4rev1 client for polling a mailbox: log in, find unseen
+//! messages, fetch them and mark them seen. Nothing else of IMAP is needed
+//! for triggers, so no IMAP library is pulled in.
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::{Context, Result, bail};
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, BufReader};
+use tokio::net::TcpStream;
+use tokio_rustls::TlsConnector;
+use tokio_rustls::rustls::pki_types::ServerName;
+use tokio_rustls::rustls::{ClientConfig, RootCertStore};
+
+use super::ImapConfig;
+
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(15);
+const COMMAND_TIMEOUT: Duration = Duration::from_secs(60);
+/// Longest response line accepted, literals excluded.
+const MAX_LINE: usize = 64 * 1024;
+/// Largest literal accepted; fetches are capped well below this.
+const MAX_LITERAL: usize = 32 * 1024 * 1024;
+
+trait Io: AsyncRead + AsyncWrite + Send + Unpin {}
+impl<T: AsyncRead + AsyncWrite + Send + Unpin> Io for T {}
+
+/// One untagged response line with the literals it carried.
+#[derive(Debug, Default)]
+struct Untagged {
+    line: String,
+    literals: Vec<Vec<u8>>,
+}
+
+pub struct ImapSession {
+    stream: BufReader<Box<dyn Io>>,
+    tag: u32,
+}
+
+impl ImapSession {
+    /// Connect, log in and select the configured mailbox.
+    pub async fn open(config: &ImapConfig) -> Result<Self> {
+        let address = (config.host.as_str(), config.port);
+        let tcp = tokio::time::timeout(CONNECT_TIMEOUT, TcpStream::connect(address))
+            .await
+            .with_context(|| format!("connecting to {}:{} timed out", config.host, config.port))?
+            .with_context(|| format!("connecting to {}:{}", config.host, config.port))?;
+        let stream: Box<dyn Io> = if config.tls {
+            let server_name = ServerName::try_from(config.host.clone())
+                .with_context(|| format!("invalid TLS server name '{}'", config.host))?;
+            let tls =
+                tokio::time::timeout(CONNECT_TIMEOUT, tls_connector()?.connect(server_name, tcp))
+                    .await
+                    .context("TLS handshake timed out")?
+                    .context("TLS handshake")?;
+            Box::new(tls)
+        } else {
+            Box::new(tcp)
+        };
+        let mut session = Self {
+            stream: BufReader::new(stream),
+            tag: 0,
+        };
+
+        let greeting = session.read_line().await.context("reading IMAP greeting")?;
+        if !greeting.starts_with("* OK") && !greeting.starts_with("* PREAUTH") {
+            bail!("unexpected IMAP greeting: {}", greeting.trim_end());
+        }
+        session
+            .command(&format!(
+                "LOGIN {} {}",
+                quote(&config.username)?,
+                quote(&config.password)?
+            ))
+            .await
+            .context("IMAP login")?;
+        session
+            .command(&format!("SELECT {}", quote(&config.mailbox)?))
+            .await
+            .with_context(|| format!("selecting mailbox '{}'", config.mailbox))?;
+        Ok(session)
+    }
+
+    /// UIDs of the unseen messages in the mailbox.
+    pub async fn unseen(&mut self) -> Result<Vec<u32>> {
+        let responses = self.command("UID SEARCH UNSEEN").await?;
+        Ok(responses
+            .iter()
+            .filter_map(|r| r.line.strip_prefix("* SEARCH"))
+            .flat_map(|ids| ids.split_whitespace().filter_map(|id| id.parse().ok()))
+            .collect())
+    }
+
+    /// The first `max_bytes` of a message, without marking it seen.
+    pub async fn fetch(&mut self, uid: u32, max_bytes: usize) -> Result<Vec<u8>> {
+        let responses = self
+            .command(&format!("UID FETCH {uid} BODY.PEEK[]<0.{max_bytes}>"))
+            .await?;
+        responses
+            .into_iter()
+            .find(|r| r.line.contains("FETCH"))
+            .and_then(|r| r.literals.into_iter().next())
+            .with_context(|| format!("message {uid} not returned"))
+    }
+
+    pub async fn mark_seen(&mut self, uid: u32) -> Result<()> {
+        self.command(&format!("UID STORE {uid} +FLAGS.SILENT (\\Seen)"))
+            .await?;
+        Ok(())
+    }
+
+    pub async fn logout(mut self) {
+        let _ = self.command("LOGOUT").await;
+    }
+
+    /// Send a command and collect its untagged responses until it completes.
+    async fn command(&mut self, command: &str) -> Result<Vec<Untagged>> {
+        self.tag += 1;
+        let tag = format!("A{}", self.tag);
+        tokio::time::timeout(COMMAND_TIMEOUT, self.exchange(&tag, command))
+            .await
+            .context("IMAP command timed out")?
+    }
+
+    async fn exchange(&mut self, tag: &str, command: &str) -> Result<Vec<Untagged>> {
+        let stream = self.stream.get_mut();
+        stream
+            .write_all(format!("{tag} {command}\r\n").as_bytes())
+            .await?;
+        stream.flush().await?;
+
+        let mut responses = Vec::new();
+        loop {
+            let response = self.read_response().await?;
+            if let Some(status) = response.line.strip_prefix(&format!("{tag} ")) {
+                if status.starts_with("OK") {
+                    return Ok(responses);
+                }
+                bail!("IMAP server answered: {}", status.trim_end());
+            }
+            responses.push(response);
+        }
+    }
+
+    /// A response line, with any literals (`{n}\r\n<n bytes>`) it contains
+    /// read in full.
+    async fn read_response(&mut self) -> Result<Untagged> {
+        let mut response = Untagged::default();
+        loop {
+            let line = self.read_line().await?;
+            let size = literal_size(&line);
+            response.line.push_str(line.trim_end_matches(['\r', '\n']));
+            let Some(size) = size else {
+                return Ok(response);
+            };
+            if size > MAX_LITERAL {
+                bail!("IMAP literal of {size} bytes is too large");
+            }
+            let mut literal = vec![0; size];
+            self.stream.read_exact(&mut literal).await?;
+            response.literals.push(literal);
+        }
+    }
+
+    async fn read_line(&mut self) -> Result<String> {
+        let mut line = Vec::new();
+        let read = (&mut self.stream)
+            .take(MAX_LINE as u64)
+            .read_until(b'\n', &mut line)
+            .await?;
+        if read == 0 {
+            bail!("IMAP server closed the connection");
+        }
+        if !line.ends_with(b"\n") {
+            bail!("IMAP response line too long");
+        }
+        Ok(String::from_utf8_lossy(&line).into_owned())
+    }
+}
+
+/// Size of the literal announced at the end of `line`, e.g. `{1234}\r\n`.
+fn literal_size(line: &str) -> Option<usize> {
+    let line = line.trim_end_matches(['\r', '\n']);
+    let open = line.strip_suffix('}')?.rfind('{')?;
+    line[open + 1..line.len() - 1].parse().ok()
+}
+
+/// An IMAP quoted string.
+fn quote(value: &str) -> Result<String> {
+    if value.contains(['\r', '\n', '\0']) {
+        bail!("IMAP strings cannot contain line breaks");
+    }
+    Ok(format!(
+        "\"{}\"",
+        value.replace('\\', "\\\\").replace('"', "\\\"")
+    ))
+}
+
+fn tls_connector() -> Result<TlsConnector> {
+    let mut roots = RootCertStore::empty();
+    roots.extend(webpki_roots::TLS_SERVER_ROOTS.iter().cloned());
+    let provider = Arc::new(tokio_rustls::rustls::crypto::ring::default_provider());
+    let tls = ClientConfig::builder_with_provider(provider)
+        .with_safe_default_protocol_versions()
+        .context("configuring TLS")?
+        .with_root_certificates(roots)
+        .with_no_client_auth();
+    Ok(TlsConnector::from(Arc::new(tls)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_literal_size() {
+        assert_eq!(
+            literal_size("* 1 FETCH (UID 7 BODY[]<0> {1234}\r\n"),
+            Some(1234)
+        );
+        assert_eq!(literal_size("* SEARCH 1 2 3\r\n"), None);
+        assert_eq!(literal_size("* OK {x}\r\n"), None);
+    }
+
+    #[test]
+    fn test_quote() {
+        assert_eq!(quote(r#"pa"ss\word"#).unwrap(), r#""pa\"ss\\word""#);
+        assert!(quote("user\r\nA2 LOGOUT").is_err());
+    }
+
+    /// Answer the next command with `reply` and return the command.
+    async fn exchange(server: &mut BufReader<tokio::io::DuplexStream>, reply: &str) -> String {
+        let mut line = String::new();
+        server.read_line(&mut line).await.unwrap();
+        server.get_mut().write_all(reply.as_bytes()).await.unwrap();
+        line
+    }
+
+    #[tokio::test]
+    async fn test_fetches_unseen_messages() {
+        let (client, server) = tokio::io::duplex(4096);
+        let script = tokio::spawn(async move {
+            let mut server = BufReader::new(server);
+            let search = exchange(&mut server, "* SEARCH 3 9\r\nA1 OK done\r\n").await;
+            assert_eq!(search, "A1 UID SEARCH UNSEEN\r\n");
+            let fetch = exchange(
+                &mut server,
+                "* 2 FETCH (UID 9 BODY[]<0> {5}\r\nhello)\r\nA2 OK done\r\n",
+            )
+            .await;
+            assert_eq!(fetch, "A2 UID FETCH 9 BODY.PEEK[]<0.100>\r\n");
+            let store = exchange(&mut server, "A3 NO read-only\r\n").await;
+            assert_eq!(store, "A3 UID STORE 9 +FLAGS.SILENT (\\Seen)\r\n");
+        });
+
+        let mut session = ImapSession {
+            stream: BufReader::new(Box::new(client) as Box<dyn Io>),
+            tag: 0,
+        };
+        assert_eq!(session.unseen().await.unwrap(), vec![3, 9]);
+        assert_eq!(session.fetch(9, 100).await.unwrap(), b"hello");
+        let err = session.mark_seen(9).await.unwrap_err();
+        assert!(err.to_string().contains("NO read-only"));
+        script.await.unwrap();
+    }
+}
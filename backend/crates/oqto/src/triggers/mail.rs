@@ -0,0 +1,394 @@
+//! Just enough RFC 5322 / MIME parsing to turn an email into a trigger
+//! event: sender, recipients, subject and the plain-text body.
+
+use base64::Engine as _;
+use base64::engine::general_purpose::STANDARD as BASE64;
+
+use crate::outbox::address_of;
+
+/// Nested multiparts deeper than this are not searched for a body.
+const MAX_MIME_DEPTH: usize = 4;
+
+/// The parts of an email a trigger uses.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ParsedMail {
+    /// Bare address of `From`.
+    pub from: Option<String>,
+    /// Bare addresses of `To`, `Cc`, `Delivered-To` and `X-Original-To`.
+    pub recipients: Vec<String>,
+    pub subject: Option<String>,
+    pub message_id: Option<String>,
+    /// The `text/plain` part, or the `text/html` part without tags.
+    pub body: String,
+}
+
+pub fn parse_mail(raw: &[u8]) -> ParsedMail {
+    let (headers, body) = split_message(raw);
+    let header = |name: &str| {
+        headers
+            .iter()
+            .find(|(n, _)| n == name)
+            .map(|(_, v)| v.as_str())
+    };
+
+    let mut recipients = Vec::new();
+    for (name, value) in &headers {
+        if matches!(
+            name.as_str(),
+            "to" | "cc" | "delivered-to" | "x-original-to"
+        ) {
+            for mailbox in split_mailboxes(value) {
+                if let Some(addr) = address_of(&mailbox)
+                    && !recipients
+                        .iter()
+                        .any(|r: &String| r.eq_ignore_ascii_case(addr))
+                {
+                    recipients.push(addr.to_string());
+                }
+            }
+        }
+    }
+
+    ParsedMail {
+        from: header("from")
+            .and_then(|from| split_mailboxes(from).into_iter().next())
+            .and_then(|mailbox| address_of(&mailbox).map(str::to_string)),
+        recipients,
+        subject: header("subject").map(decode_words),
+        message_id: header("message-id").map(|id| id.trim().to_string()),
+        body: text_body(&headers, body, 0)
+            .unwrap_or_default()
+            .trim()
+            .to_string(),
+    }
+}
+
+/// Unfolded headers (names lowercased) and the body.
+fn split_message(raw: &[u8]) -> (Vec<(String, String)>, &[u8]) {
+    let (head, body) = match find(raw, b"\r\n\r\n") {
+        Some(i) => (&raw[..i], &raw[i + 4..]),
+        None => match find(raw, b"\n\n") {
+            Some(i) => (&raw[..i], &raw[i + 2..]),
+            None => (raw, &raw[raw.len()..]),
+        },
+    };
+    let mut headers: Vec<(String, String)> = Vec::new();
+    for line in String::from_utf8_lossy(head).lines() {
+        if line.starts_with([' ', '\t']) {
+            if let Some((_, value)) = headers.last_mut() {
+                value.push(' ');
+                value.push_str(line.trim());
+            }
+        } else if let Some((name, value)) = line.split_once(':') {
+            headers.push((name.trim().to_ascii_lowercase(), value.trim().to_string()));
+        }
+    }
+    (headers, body)
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack.windows(needle.len()).position(|w| w == needle)
+}
+
+/// Decoded text of the best body part.
+fn text_body(headers: &[(String, String)], body: &[u8], depth: usize) -> Option<String> {
+    let content_type = headers
+        .iter()
+        .find(|(n, _)| n == "content-type")
+        .map(|(_, v)| v.as_str())
+        .unwrap_or("text/plain");
+    let mime = content_type
+        .split(';')
+        .next()
+        .unwrap_or_default()
+        .trim()
+        .to_ascii_lowercase();
+
+    if mime.starts_with("multipart/") {
+        if depth >= MAX_MIME_DEPTH {
+            return None;
+        }
+        let boundary = param(content_type, "boundary")?;
+        let parts: Vec<_> = split_parts(body, &boundary)
+            .into_iter()
+            .map(split_message)
+            .collect();
+        // Prefer plain text anywhere in the alternatives over HTML.
+        return parts
+            .iter()
+            .find_map(|(h, b)| {
+                let plain = h
+                    .iter()
+                    .find(|(n, _)| n == "content-type")
+                    .is_none_or(|(_, v)| v.to_ascii_lowercase().starts_with("text/plain"));
+                if plain {
+                    text_body(h, b, depth + 1)
+                } else {
+                    None
+                }
+            })
+            .or_else(|| parts.iter().find_map(|(h, b)| text_body(h, b, depth + 1)));
+    }
+
+    let is_attachment = headers.iter().any(|(n, v)| {
+        n == "content-disposition" && v.to_ascii_lowercase().starts_with("attachment")
+    });
+    if is_attachment || !(mime == "text/plain" || mime == "text/html") {
+        return None;
+    }
+    let encoding = headers
+        .iter()
+        .find(|(n, _)| n == "content-transfer-encoding")
+        .map(|(_, v)| v.trim().to_ascii_lowercase())
+        .unwrap_or_default();
+    let bytes = match encoding.as_str() {
+        "base64" => {
+            let compact: Vec<u8> = body
+                .iter()
+                .copied()
+                .filter(|b| !b.is_ascii_whitespace())
+                .collect();
+            BASE64.decode(compact).ok()?
+        }
+        "quoted-printable" => decode_quoted_printable(body, false),
+        _ => body.to_vec(),
+    };
+    let text = decode_charset(&bytes, param(content_type, "charset").as_deref());
+    Some(if mime == "text/html" {
+        strip_tags(&text)
+    } else {
+        text
+    })
+}
+
+/// The parts of a multipart body, without the boundary lines.
+fn split_parts<'a>(body: &'a [u8], boundary: &str) -> Vec<&'a [u8]> {
+    let delimiter = format!("--{boundary}");
+    let mut parts = Vec::new();
+    let mut rest = body;
+    let Some(start) = find(rest, delimiter.as_bytes()) else {
+        return parts;
+    };
+    rest = &rest[start + delimiter.len()..];
+    while !rest.starts_with(b"--") {
+        let Some(end) = find(rest, delimiter.as_bytes()) else {
+            break;
+        };
+        let part = &rest[..end];
+        let part = part
+            .strip_prefix(b"\r\n")
+            .or_else(|| part.strip_prefix(b"\n"))
+            .unwrap_or(part);
+        parts.push(part);
+        rest = &rest[end + delimiter.len()..];
+    }
+    parts
+}
+
+/// A `name=value` parameter of a structured header.
+fn param(value: &str, name: &str) -> Option<String> {
+    value.split(';').skip(1).find_map(|p| {
+        let (key, value) = p.split_once('=')?;
+        key.trim()
+            .eq_ignore_ascii_case(name)
+            .then(|| value.trim().trim_matches('"').to_string())
+    })
+}
+
+/// Mailboxes of an address list; commas inside quotes or angle brackets do
+/// not separate.
+fn split_mailboxes(value: &str) -> Vec<String> {
+    let mut mailboxes = Vec::new();
+    let mut current = String::new();
+    let (mut quoted, mut angle) = (false, false);
+    for c in value.chars() {
+        match c {
+            '"' => quoted = !quoted,
+            '<' if !quoted => angle = true,
+            '>' if !quoted => angle = false,
+            ',' if !quoted && !angle => {
+                mailboxes.push(std::mem::take(&mut current));
+                continue;
+            }
+            _ => {}
+        }
+        current.push(c);
+    }
+    mailboxes.push(current);
+    mailboxes
+        .into_iter()
+        .map(|m| m.trim().to_string())
+        .filter(|m| !m.is_empty())
+        .collect()
+}
+
+/// Decode RFC 2047 encoded words (`=?utf-8?B?...?=`, `=?utf-8?Q?...?=`).
+fn decode_words(value: &str) -> String {
+    let mut out = String::new();
+    let mut rest = value;
+    let mut after_word = false;
+    while let Some(start) = rest.find("=?") {
+        let word = rest[start + 2..].splitn(3, '?').collect::<Vec<_>>();
+        let decoded = match word.as_slice() {
+            [charset, encoding, tail] => tail.find("?=").and_then(|end| {
+                let text = &tail[..end];
+                let bytes = match encoding.to_ascii_uppercase().as_str() {
+                    "B" => BASE64.decode(text).ok()?,
+                    "Q" => decode_quoted_printable(text.as_bytes(), true),
+                    _ => return None,
+                };
+                let len = charset.len() + encoding.len() + end + 6;
+                Some((decode_charset(&bytes, Some(charset)), len))
+            }),
+            _ => None,
+        };
+        let Some((text, len)) = decoded else {
+            out.push_str(&rest[..start + 2]);
+            rest = &rest[start + 2..];
+            after_word = false;
+            continue;
+        };
+        // Whitespace between adjacent encoded words is not part of the text.
+        let gap = &rest[..start];
+        if !(after_word && gap.trim().is_empty()) {
+            out.push_str(gap);
+        }
+        out.push_str(&text);
+        rest = &rest[start + len..];
+        after_word = true;
+    }
+    out.push_str(rest);
+    out
+}
+
+fn decode_quoted_printable(input: &[u8], header: bool) -> Vec<u8> {
+    let mut out = Vec::with_capacity(input.len());
+    let mut i = 0;
+    while i < input.len() {
+        match input[i] {
+            b'=' if input[i + 1..].starts_with(b"\r\n") => i += 3,
+            b'=' if input[i + 1..].starts_with(b"\n") => i += 2,
+            b'=' if i + 2 < input.len() => {
+                match std::str::from_utf8(&input[i + 1..i + 3])
+                    .ok()
+                    .and_then(|hex| u8::from_str_radix(hex, 16).ok())
+                {
+                    Some(byte) => {
+                        out.push(byte);
+                        i += 3;
+                    }
+                    None => {
+                        out.push(b'=');
+                        i += 1;
+                    }
+                }
+            }
+            b'_' if header => {
+                out.push(b' ');
+                i += 1;
+            }
+            byte => {
+                out.push(byte);
+                i += 1;
+            }
+        }
+    }
+    out
+}
+
+/// UTF-8 (lossy), or Latin-1 for the ISO-8859-1 family.
+fn decode_charset(bytes: &[u8], charset: Option<&str>) -> String {
+    match charset.map(str::to_ascii_lowercase).as_deref() {
+        Some("iso-8859-1" | "latin1" | "windows-1252" | "us-ascii") => {
+            bytes.iter().map(|&b| char::from(b)).collect()
+        }
+        _ => String::from_utf8_lossy(bytes).into_owned(),
+    }
+}
+
+/// Text of an HTML part, with tags removed and line breaks kept.
+fn strip_tags(html: &str) -> String {
+    let mut out = String::new();
+    let mut in_tag = false;
+    let mut tag = String::new();
+    for c in html.chars() {
+        match c {
+            '<' => {
+                in_tag = true;
+                tag.clear();
+            }
+            '>' if in_tag => {
+                in_tag = false;
+                let name = tag.trim_start_matches('/').to_ascii_lowercase();
+                if name.starts_with("br") || name.starts_with("p") || name.starts_with("div") {
+                    out.push('\n');
+                }
+            }
+            _ if in_tag => tag.push(c),
+            _ => out.push(c),
+        }
+    }
+    out.replace("&nbsp;", " ")
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&amp;", "&")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_plain_mail() {
+        let raw = b"From: \"Smith, Bob\" <bob@example.com>\r\n\
+            To: agent+trg_abc@example.org, Carol <carol@example.org>\r\n\
+            Subject: =?utf-8?B?R3LDvMOfZQ==?=\r\n =?utf-8?Q?_aus_Berlin?=\r\n\
+            Message-ID: <m1@example.com>\r\n\
+            Content-Type: text/plain; charset=utf-8\r\n\
+            Content-Transfer-Encoding: quoted-printable\r\n\
+            \r\n\
+            Bitte Rechnung pr=C3=BCfen, danke=\r\n!\r\n";
+        let mail = parse_mail(raw);
+        assert_eq!(mail.from.as_deref(), Some("bob@example.com"));
+        assert_eq!(
+            mail.recipients,
+            vec!["agent+trg_abc@example.org", "carol@example.org"]
+        );
+        assert_eq!(mail.subject.as_deref(), Some("Grüße aus Berlin"));
+        assert_eq!(mail.message_id.as_deref(), Some("<m1@example.com>"));
+        assert_eq!(mail.body, "Bitte Rechnung prüfen, danke!");
+    }
+
+    #[test]
+    fn test_parse_multipart_prefers_plain_text() {
+        let raw = b"From: bob@example.com\n\
+            To: agent+trg_abc@example.org\n\
+            Content-Type: multipart/mixed; boundary=\"outer\"\n\
+            \n\
+            --outer\n\
+            Content-Type: multipart/alternative; boundary=inner\n\
+            \n\
+            --inner\n\
+            Content-Type: text/html\n\
+            \n\
+            <p>Hello <b>html</b></p>\n\
+            --inner\n\
+            Content-Type: text/plain; charset=utf-8\n\
+            Content-Transfer-Encoding: base64\n\
+            \n\
+            SGVsbG8gcGxhaW4=\n\
+            --inner--\n\
+            --outer\n\
+            Content-Type: text/plain\n\
+            Content-Disposition: attachment; filename=notes.txt\n\
+            \n\
+            attached\n\
+            --outer--\n";
+        let mail = parse_mail(raw);
+        assert_eq!(mail.body, "Hello plain");
+
+        let html_only = b"Content-Type: text/html\n\n<div>Hi &amp; bye</div>";
+        assert_eq!(parse_mail(html_only).body, "Hi & bye");
+    }
+}
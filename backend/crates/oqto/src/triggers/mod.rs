@@ -0,0 +1,381 @@
+//! Inbound triggers: sessions started by external events.
+//!
+//! A trigger maps an event to a prompt in one of the user's workspaces. An
+//! event is a webhook call to `POST /api/triggers/{token}` or, with the
+//! IMAP poller configured, an email to the trigger's subaddress of the
+//! polled mailbox (`agent+<token>@example.com`). Each event starts a fresh
+//! session in the background, like an agent schedule run, and the agent's
+//! final answer can be delivered back to a webhook and/or by email to the
+//! sender. Senders can be restricted per trigger; emails are only accepted
+//! from allowlisted senders.
+
+mod imap;
+mod mail;
+mod models;
+mod repository;
+
+pub use mail::ParsedMail;
+pub use models::{
+    AgentTrigger, ImapConfig, SaveTriggerRequest, TriggerEvent, TriggerRun, TriggerRunStatus,
+    TriggerSource, TriggerWithToken, TriggersConfig,
+};
+pub use repository::TriggerRepository;
+
+use std::net::IpAddr;
+use std::time::Duration;
+
+use anyhow::{Context, Result, bail};
+use sha2::{Digest, Sha256};
+use tracing::warn;
+
+use crate::outbox::{OutboxContent, address_of, format_email, sendmail};
+use imap::ImapSession;
+use mail::parse_mail;
+
+const TOKEN_PREFIX: &str = "trg_";
+const DELIVERY_TIMEOUT: Duration = Duration::from_secs(30);
+/// Longest result text sent back.
+const MAX_RESULT_CHARS: usize = 100_000;
+
+/// Generate a trigger token and its display prefix. Tokens are lowercase
+/// hex so they survive mail systems that lowercase addresses.
+pub fn generate_token() -> (String, String) {
+    let bytes: [u8; 20] = rand::random();
+    let token = format!("{TOKEN_PREFIX}{}", hex::encode(bytes));
+    let prefix = token.chars().take(TOKEN_PREFIX.len() + 6).collect();
+    (token, prefix)
+}
+
+/// Hash a token for storage/lookup.
+pub fn hash_token(token: &str) -> String {
+    hex::encode(Sha256::digest(token.trim().to_ascii_lowercase().as_bytes()))
+}
+
+/// The trigger token in the subaddress of `address` (`local+<token>@domain`).
+pub fn token_of_address(address: &str) -> Option<&str> {
+    let (local, _) = address.rsplit_once('@')?;
+    let (_, tag) = local.split_once('+')?;
+    tag.starts_with(TOKEN_PREFIX).then_some(tag)
+}
+
+/// Render a trigger prompt. `{{sender}}`, `{{subject}}` and `{{body}}` are
+/// replaced with the event's, `{{payload}}` with the JSON body and
+/// `{{payload.a.b}}` with a field of it. Unknown placeholders become empty.
+pub fn render_prompt(prompt: &str, event: &TriggerEvent) -> String {
+    let mut out = String::with_capacity(prompt.len() + event.body.len());
+    let mut rest = prompt;
+    while let Some(start) = rest.find("{{") {
+        let Some(end) = rest[start..].find("}}") else {
+            break;
+        };
+        out.push_str(&rest[..start]);
+        let name = rest[start + 2..start + end].trim();
+        match name {
+            "sender" => out.push_str(event.sender.as_deref().unwrap_or_default()),
+            "subject" => out.push_str(event.subject.as_deref().unwrap_or_default()),
+            "body" => out.push_str(&event.body),
+            "payload" => match &event.payload {
+                Some(payload) => out.push_str(&payload.to_string()),
+                None => out.push_str(&event.body),
+            },
+            _ => {
+                if let Some(path) = name.strip_prefix("payload.")
+                    && let Some(value) = event
+                        .payload
+                        .as_ref()
+                        .and_then(|p| path.split('.').try_fold(p, |v, key| v.get(key)))
+                {
+                    match value {
+                        serde_json::Value::String(s) => out.push_str(s),
+                        serde_json::Value::Null => {}
+                        other => out.push_str(&other.to_string()),
+                    }
+                }
+            }
+        }
+        rest = &rest[start + end + 2..];
+    }
+    out.push_str(rest);
+    out
+}
+
+/// Whether `allowed` lets an event from `sender` in. Email events need an
+/// allowlisted address or `@domain`. Webhook calls are checked against the
+/// IP addresses in the list, if there are any.
+pub fn sender_allowed(allowed: &[String], source: TriggerSource, sender: Option<&str>) -> bool {
+    match source {
+        TriggerSource::Email => {
+            let Some(sender) = sender.and_then(address_of) else {
+                return false;
+            };
+            let sender = sender.to_ascii_lowercase();
+            allowed.iter().any(|entry| {
+                let entry = entry.trim().to_ascii_lowercase();
+                match entry.strip_prefix('@') {
+                    Some(domain) => sender.rsplit_once('@').is_some_and(|(_, d)| d == domain),
+                    None => entry == sender,
+                }
+            })
+        }
+        TriggerSource::Webhook => {
+            let ips: Vec<IpAddr> = allowed
+                .iter()
+                .filter_map(|entry| entry.trim().parse().ok())
+                .collect();
+            ips.is_empty()
+                || sender
+                    .and_then(|s| s.parse::<IpAddr>().ok())
+                    .is_some_and(|ip| ips.contains(&crate::net::canonical_ip(ip)))
+        }
+    }
+}
+
+/// Whether an allowlist entry is an address, `@domain` or IP address.
+pub fn valid_sender_entry(entry: &str) -> bool {
+    let entry = entry.trim();
+    entry.parse::<IpAddr>().is_ok()
+        || entry
+            .strip_prefix('@')
+            .is_some_and(|d| d.contains('.') && !d.contains(['@', ' ']))
+        || address_of(entry) == Some(entry)
+}
+
+/// Result of a run, as delivered back.
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct TriggerResult<'a> {
+    pub trigger: &'a str,
+    pub run_id: &'a str,
+    pub session_id: &'a str,
+    pub status: TriggerRunStatus,
+    pub result: Option<&'a str>,
+    pub error: Option<&'a str>,
+}
+
+/// Trigger definitions, runs and result delivery.
+pub struct TriggerService {
+    repo: TriggerRepository,
+    config: TriggersConfig,
+    client: reqwest::Client,
+}
+
+impl TriggerService {
+    pub fn new(repo: TriggerRepository, config: TriggersConfig) -> Result<Self> {
+        if config.reply_email.enabled && address_of(&config.reply_email.from).is_none() {
+            bail!("triggers.reply_email.from must be an email address");
+        }
+        if config.imap.enabled && (config.imap.host.is_empty() || config.imap.username.is_empty()) {
+            bail!("triggers.imap needs host and username");
+        }
+        let client = reqwest::Client::builder()
+            .timeout(DELIVERY_TIMEOUT)
+            .build()
+            .context("building trigger HTTP client")?;
+        Ok(Self {
+            repo,
+            config,
+            client,
+        })
+    }
+
+    pub fn config(&self) -> &TriggersConfig {
+        &self.config
+    }
+
+    pub fn repo(&self) -> &TriggerRepository {
+        &self.repo
+    }
+
+    /// Deliver a run's result to the trigger's webhook and, for email
+    /// events, back to the sender. Returns `sent` or what went wrong; None
+    /// when nothing is configured.
+    pub async fn deliver(
+        &self,
+        trigger: &AgentTrigger,
+        event: &TriggerEvent,
+        source: TriggerSource,
+        result: &TriggerResult<'_>,
+    ) -> Option<String> {
+        let mut outcomes = Vec::new();
+        if let Some(url) = &trigger.reply_webhook_url {
+            outcomes.push(
+                self.post_result(url, result)
+                    .await
+                    .map_err(|e| format!("webhook: {e:#}")),
+            );
+        }
+        if trigger.reply_email && source == TriggerSource::Email {
+            outcomes.push(
+                self.email_result(event, result)
+                    .await
+                    .map_err(|e| format!("email: {e:#}")),
+            );
+        }
+        if outcomes.is_empty() {
+            return None;
+        }
+        let errors: Vec<_> = outcomes.into_iter().filter_map(Result::err).collect();
+        for error in &errors {
+            warn!(trigger = %trigger.name, "Failed to deliver trigger result: {error}");
+        }
+        Some(if errors.is_empty() {
+            "sent".to_string()
+        } else {
+            errors.join("; ")
+        })
+    }
+
+    async fn post_result(&self, url: &str, result: &TriggerResult<'_>) -> Result<()> {
+        self.client
+            .post(url)
+            .json(result)
+            .send()
+            .await
+            .context("posting result")?
+            .error_for_status()
+            .context("posting result")?;
+        Ok(())
+    }
+
+    async fn email_result(&self, event: &TriggerEvent, result: &TriggerResult<'_>) -> Result<()> {
+        let email = &self.config.reply_email;
+        if !email.enabled {
+            bail!("email replies are not enabled");
+        }
+        let recipient = event
+            .sender
+            .as_deref()
+            .and_then(address_of)
+            .context("sender has no address")?;
+        let domain = recipient
+            .rsplit_once('@')
+            .map(|(_, d)| d)
+            .unwrap_or_default();
+        if !email.allowed_domains.is_empty()
+            && !email
+                .allowed_domains
+                .iter()
+                .any(|d| d.eq_ignore_ascii_case(domain))
+        {
+            bail!("recipient domain {domain} is not allowed");
+        }
+
+        let subject = event.subject.as_deref().unwrap_or("your request");
+        let subject = if subject.to_ascii_lowercase().starts_with("re:") {
+            subject.to_string()
+        } else {
+            format!("Re: {subject}")
+        };
+        let body = match (result.result, result.error) {
+            (Some(text), _) => text.chars().take(MAX_RESULT_CHARS).collect(),
+            (None, Some(error)) => format!("The agent could not complete this request: {error}"),
+            (None, None) => "The agent finished without a reply.".to_string(),
+        };
+        let content = OutboxContent {
+            recipients: vec![recipient.to_string()],
+            cc: Vec::new(),
+            subject: Some(subject.replace(['\r', '\n'], " ")),
+            body,
+        };
+        let mut headers = vec![("X-Oqto-Trigger-Run", result.run_id.to_string())];
+        if let Some(message_id) = event
+            .message_id
+            .as_deref()
+            .filter(|id| !id.contains(['\r', '\n']))
+        {
+            headers.push(("In-Reply-To", message_id.to_string()));
+            headers.push(("References", message_id.to_string()));
+        }
+        let (_, rfc822) = format_email(&email.from, &content, &headers)?;
+        sendmail(email, &content.recipients, &rfc822).await
+    }
+
+    /// Fetch the unseen messages of the IMAP mailbox and mark them seen.
+    /// Messages are marked before they are handled, so a crash drops an
+    /// email rather than running it twice.
+    pub async fn poll_mailbox(&self) -> Result<Vec<ParsedMail>> {
+        let mut session = ImapSession::open(&self.config.imap).await?;
+        let mut mails = Vec::new();
+        let result = async {
+            for uid in session.unseen().await? {
+                let raw = session.fetch(uid, self.config.max_payload_bytes).await?;
+                session.mark_seen(uid).await?;
+                mails.push(parse_mail(&raw));
+            }
+            anyhow::Ok(())
+        }
+        .await;
+        session.logout().await;
+        result.map(|()| mails)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tokens() {
+        let (token, prefix) = generate_token();
+        assert_eq!(token.len(), TOKEN_PREFIX.len() + 40);
+        assert!(token.starts_with(&prefix));
+        assert_eq!(hash_token(&token), hash_token(&token.to_ascii_uppercase()));
+
+        assert_eq!(
+            token_of_address("agent+trg_0a1b@example.com"),
+            Some("trg_0a1b")
+        );
+        assert_eq!(token_of_address("agent+news@example.com"), None);
+        assert_eq!(token_of_address("agent@example.com"), None);
+    }
+
+    #[test]
+    fn test_render_prompt() {
+        let event = TriggerEvent {
+            sender: Some("bob@example.com".to_string()),
+            subject: Some("Build failed".to_string()),
+            body: r#"{"repo":{"name":"api"},"run":42}"#.to_string(),
+            payload: Some(serde_json::json!({"repo": {"name": "api"}, "run": 42})),
+            message_id: None,
+        };
+        assert_eq!(
+            render_prompt(
+                "From {{sender}}: {{ subject }} in {{payload.repo.name}} #{{payload.run}}{{missing}}",
+                &event
+            ),
+            "From bob@example.com: Build failed in api #42"
+        );
+        assert_eq!(
+            render_prompt("Payload: {{payload}} {{unclosed", &event),
+            r#"Payload: {"repo":{"name":"api"},"run":42} {{unclosed"#
+        );
+    }
+
+    #[test]
+    fn test_sender_allowed() {
+        let allowed = vec!["alice@example.com".to_string(), "@corp.example".to_string()];
+        let email = TriggerSource::Email;
+        assert!(sender_allowed(&allowed, email, Some("Alice@Example.com")));
+        assert!(sender_allowed(&allowed, email, Some("bob@corp.example")));
+        assert!(!sender_allowed(
+            &allowed,
+            email,
+            Some("bob@evil.corp.example")
+        ));
+        assert!(!sender_allowed(&allowed, email, Some("eve@example.com")));
+        assert!(!sender_allowed(&[], email, Some("alice@example.com")));
+
+        let webhook = TriggerSource::Webhook;
+        assert!(sender_allowed(&allowed, webhook, Some("203.0.113.9")));
+        let ips = vec!["203.0.113.9".to_string()];
+        assert!(sender_allowed(&ips, webhook, Some("203.0.113.9")));
+        assert!(sender_allowed(&ips, webhook, Some("::ffff:203.0.113.9")));
+        assert!(!sender_allowed(&ips, webhook, Some("198.51.100.1")));
+        assert!(!sender_allowed(&ips, webhook, None));
+
+        assert!(valid_sender_entry("@example.com"));
+        assert!(valid_sender_entry("alice@example.com"));
+        assert!(valid_sender_entry("2001:db8::1"));
+        assert!(!valid_sender_entry("example.com"));
+        assert!(!valid_sender_entry("Alice <alice@example.com>"));
+    }
+}
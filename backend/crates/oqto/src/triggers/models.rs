@@ -0,0 +1,196 @@
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+
+use crate::outbox::EmailSenderConfig;
+
+/// Inbound trigger configuration.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TriggersConfig {
+    /// Accept `POST /api/triggers/{token}` and let users define triggers.
+    pub enabled: bool,
+    /// Triggers a user can have.
+    pub max_triggers_per_user: i64,
+    /// Queued and running runs a trigger may have; further events get 429.
+    pub max_active_runs_per_trigger: i64,
+    /// Largest accepted webhook body or email, in bytes.
+    pub max_payload_bytes: usize,
+    /// Runs still working after this long are stopped and failed, unless
+    /// the trigger sets its own timeout.
+    pub run_timeout_secs: u64,
+    /// Replies to email senders, sent with the host's sendmail.
+    pub reply_email: EmailSenderConfig,
+    /// Mailbox polled for emails to triggers.
+    pub imap: ImapConfig,
+}
+
+impl Default for TriggersConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            max_triggers_per_user: 20,
+            max_active_runs_per_trigger: 5,
+            max_payload_bytes: 256 * 1024,
+            run_timeout_secs: 3600,
+            reply_email: EmailSenderConfig::default(),
+            imap: ImapConfig::default(),
+        }
+    }
+}
+
+/// IMAP mailbox whose unseen messages fire triggers. A message fires the
+/// trigger whose token is the subaddress of a recipient, e.g.
+/// `agent+trg_...@example.com`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ImapConfig {
+    pub enabled: bool,
+    pub host: String,
+    pub port: u16,
+    /// Implicit TLS (IMAPS). Plain IMAP is only for a server on localhost.
+    pub tls: bool,
+    pub username: String,
+    pub password: String,
+    pub mailbox: String,
+    pub poll_interval_secs: u64,
+}
+
+impl Default for ImapConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            host: String::new(),
+            port: 993,
+            tls: true,
+            username: String::new(),
+            password: String::new(),
+            mailbox: "INBOX".to_string(),
+            poll_interval_secs: 60,
+        }
+    }
+}
+
+/// Where an event came from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, sqlx::Type)]
+#[serde(rename_all = "snake_case")]
+#[sqlx(type_name = "text", rename_all = "snake_case")]
+pub enum TriggerSource {
+    Webhook,
+    Email,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, sqlx::Type)]
+#[serde(rename_all = "snake_case")]
+#[sqlx(type_name = "text", rename_all = "snake_case")]
+pub enum TriggerRunStatus {
+    /// Waiting for a background slot.
+    Queued,
+    Running,
+    Succeeded,
+    Failed,
+}
+
+/// A user-defined mapping from external events to an agent prompt.
+#[derive(Debug, Clone, Serialize)]
+pub struct AgentTrigger {
+    pub id: String,
+    pub user_id: String,
+    pub name: String,
+    pub token_prefix: String,
+    pub workspace_path: String,
+    /// Rendered with the event's `{{sender}}`, `{{subject}}`, `{{body}}`,
+    /// `{{payload}}` and `{{payload.<field>}}`.
+    pub prompt: String,
+    /// Prompt template invoked as `/<template> <prompt>`.
+    pub template: Option<String>,
+    pub harness: Option<String>,
+    pub provider: Option<String>,
+    pub model: Option<String>,
+    /// Overrides `run_timeout_secs`.
+    pub timeout_secs: Option<i64>,
+    /// Email addresses, `@domain`s and IP addresses events are accepted from.
+    pub allowed_senders: Vec<String>,
+    /// Receives the result of every run as JSON.
+    pub reply_webhook_url: Option<String>,
+    /// Email the result back to the sender of email events.
+    pub reply_email: bool,
+    pub enabled: bool,
+    pub last_fired_at: Option<String>,
+    pub created_at: String,
+    pub updated_at: String,
+}
+
+/// Body of `POST /api/me/triggers` and `PUT /api/me/triggers/{name}`.
+#[derive(Debug, Clone, Deserialize)]
+pub struct SaveTriggerRequest {
+    /// Taken from the path on update.
+    #[serde(default)]
+    pub name: Option<String>,
+    pub workspace_path: String,
+    pub prompt: String,
+    #[serde(default)]
+    pub template: Option<String>,
+    #[serde(default)]
+    pub harness: Option<String>,
+    #[serde(default)]
+    pub provider: Option<String>,
+    #[serde(default)]
+    pub model: Option<String>,
+    #[serde(default)]
+    pub timeout_secs: Option<i64>,
+    #[serde(default)]
+    pub allowed_senders: Vec<String>,
+    #[serde(default)]
+    pub reply_webhook_url: Option<String>,
+    #[serde(default)]
+    pub reply_email: bool,
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+}
+
+fn default_enabled() -> bool {
+    true
+}
+
+/// A trigger together with its token, which is only shown on creation and
+/// rotation.
+#[derive(Debug, Clone, Serialize)]
+pub struct TriggerWithToken {
+    #[serde(flatten)]
+    pub trigger: AgentTrigger,
+    pub token: String,
+    /// Path external services post events to.
+    pub url: String,
+}
+
+/// One event handled by a trigger.
+#[derive(Debug, Clone, Serialize, FromRow)]
+pub struct TriggerRun {
+    pub id: String,
+    pub trigger_id: String,
+    pub source: TriggerSource,
+    pub sender: Option<String>,
+    pub subject: Option<String>,
+    pub status: TriggerRunStatus,
+    pub session_id: String,
+    pub error: Option<String>,
+    /// `sent`, or why the result could not be delivered.
+    pub delivery: Option<String>,
+    pub created_at: String,
+    pub started_at: Option<String>,
+    pub finished_at: Option<String>,
+}
+
+/// An external event, as handed to a trigger.
+#[derive(Debug, Clone, Default)]
+pub struct TriggerEvent {
+    /// Email address of the sender, or the caller's IP for webhooks.
+    pub sender: Option<String>,
+    pub subject: Option<String>,
+    /// Plain-text body of an email, or the raw webhook body.
+    pub body: String,
+    /// JSON webhook body.
+    pub payload: Option<serde_json::Value>,
+    /// Message-ID of an email, referenced by the reply.
+    pub message_id: Option<String>,
+}
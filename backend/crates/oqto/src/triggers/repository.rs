@@ -0,0 +1,456 @@
+use anyhow::{Context, Result};
+use chrono::{DateTime, SecondsFormat, Utc};
+use sqlx::FromRow;
+
+use crate::db::{DbPool, on_pool};
+
+use super::{
+    AgentTrigger, SaveTriggerRequest, TriggerEvent, TriggerRun, TriggerRunStatus, TriggerSource,
+};
+
+const TRIGGER_COLUMNS: &str = "id, user_id, name, token_prefix, workspace_path, prompt, template, \
+     harness, provider, model, timeout_secs, allowed_senders, reply_webhook_url, reply_email, \
+     enabled, last_fired_at, created_at, updated_at";
+
+const RUN_COLUMNS: &str = "id, trigger_id, source, sender, subject, status, session_id, error, \
+     delivery, created_at, started_at, finished_at";
+
+/// Most runs returned by one list call.
+const MAX_RUNS_LIMIT: i64 = 200;
+
+fn timestamp(at: DateTime<Utc>) -> String {
+    at.to_rfc3339_opts(SecondsFormat::Millis, true)
+}
+
+#[derive(Debug, Clone, FromRow)]
+struct TriggerRow {
+    id: String,
+    user_id: String,
+    name: String,
+    token_prefix: String,
+    workspace_path: String,
+    prompt: String,
+    template: Option<String>,
+    harness: Option<String>,
+    provider: Option<String>,
+    model: Option<String>,
+    timeout_secs: Option<i64>,
+    allowed_senders: String,
+    reply_webhook_url: Option<String>,
+    reply_email: bool,
+    enabled: bool,
+    last_fired_at: Option<String>,
+    created_at: String,
+    updated_at: String,
+}
+
+impl From<TriggerRow> for AgentTrigger {
+    fn from(row: TriggerRow) -> Self {
+        Self {
+            id: row.id,
+            user_id: row.user_id,
+            name: row.name,
+            token_prefix: row.token_prefix,
+            workspace_path: row.workspace_path,
+            prompt: row.prompt,
+            template: row.template,
+            harness: row.harness,
+            provider: row.provider,
+            model: row.model,
+            timeout_secs: row.timeout_secs,
+            allowed_senders: serde_json::from_str(&row.allowed_senders).unwrap_or_default(),
+            reply_webhook_url: row.reply_webhook_url,
+            reply_email: row.reply_email,
+            enabled: row.enabled,
+            last_fired_at: row.last_fired_at,
+            created_at: row.created_at,
+            updated_at: row.updated_at,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct TriggerRepository {
+    pool: DbPool,
+}
+
+impl TriggerRepository {
+    pub fn new(pool: DbPool) -> Self {
+        Self { pool }
+    }
+
+    pub async fn list(&self, user_id: &str) -> Result<Vec<AgentTrigger>> {
+        let sql = format!(
+            "SELECT {TRIGGER_COLUMNS} FROM agent_triggers WHERE user_id = $1 ORDER BY name"
+        );
+        let rows = on_pool!(&self.pool, |pool| sqlx::query_as::<_, TriggerRow>(&sql)
+            .bind(user_id)
+            .fetch_all(pool)
+            .await)
+        .context("list triggers")?;
+        Ok(rows.into_iter().map(AgentTrigger::from).collect())
+    }
+
+    pub async fn get(&self, user_id: &str, name: &str) -> Result<Option<AgentTrigger>> {
+        let sql = format!(
+            "SELECT {TRIGGER_COLUMNS} FROM agent_triggers WHERE user_id = $1 AND name = $2"
+        );
+        let row = on_pool!(&self.pool, |pool| sqlx::query_as::<_, TriggerRow>(&sql)
+            .bind(user_id)
+            .bind(name)
+            .fetch_optional(pool)
+            .await)
+        .context("get trigger")?;
+        Ok(row.map(AgentTrigger::from))
+    }
+
+    /// The trigger a token belongs to.
+    pub async fn get_by_token_hash(&self, token_hash: &str) -> Result<Option<AgentTrigger>> {
+        let sql = format!("SELECT {TRIGGER_COLUMNS} FROM agent_triggers WHERE token_hash = $1");
+        let row = on_pool!(&self.pool, |pool| sqlx::query_as::<_, TriggerRow>(&sql)
+            .bind(token_hash)
+            .fetch_optional(pool)
+            .await)
+        .context("get trigger by token")?;
+        Ok(row.map(AgentTrigger::from))
+    }
+
+    pub async fn count(&self, user_id: &str) -> Result<i64> {
+        on_pool!(&self.pool, |pool| sqlx::query_scalar(
+            "SELECT COUNT(*) FROM agent_triggers WHERE user_id = $1"
+        )
+        .bind(user_id)
+        .fetch_one(pool)
+        .await)
+        .context("count triggers")
+    }
+
+    /// Create the trigger `name` with the given token.
+    pub async fn create(
+        &self,
+        user_id: &str,
+        name: &str,
+        request: &SaveTriggerRequest,
+        token_hash: &str,
+        token_prefix: &str,
+    ) -> Result<AgentTrigger> {
+        let now = timestamp(Utc::now());
+        let allowed_senders = serde_json::to_string(&request.allowed_senders)?;
+        let sql = format!(
+            r#"INSERT INTO agent_triggers
+                   (id, user_id, name, token_hash, token_prefix, workspace_path, prompt, template,
+                    harness, provider, model, timeout_secs, allowed_senders, reply_webhook_url,
+                    reply_email, enabled, created_at, updated_at)
+               VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15, $16,
+                       $17, $17)
+               RETURNING {TRIGGER_COLUMNS}"#
+        );
+        let id = format!("trg_{}", uuid::Uuid::new_v4().simple());
+        let row = on_pool!(&self.pool, |pool| sqlx::query_as::<_, TriggerRow>(&sql)
+            .bind(&id)
+            .bind(user_id)
+            .bind(name)
+            .bind(token_hash)
+            .bind(token_prefix)
+            .bind(&request.workspace_path)
+            .bind(&request.prompt)
+            .bind(&request.template)
+            .bind(&request.harness)
+            .bind(&request.provider)
+            .bind(&request.model)
+            .bind(request.timeout_secs)
+            .bind(&allowed_senders)
+            .bind(&request.reply_webhook_url)
+            .bind(request.reply_email)
+            .bind(request.enabled)
+            .bind(&now)
+            .fetch_one(pool)
+            .await)
+        .context("create trigger")?;
+        Ok(row.into())
+    }
+
+    /// Replace a trigger's settings. Its token stays the same.
+    pub async fn update(
+        &self,
+        user_id: &str,
+        name: &str,
+        request: &SaveTriggerRequest,
+    ) -> Result<Option<AgentTrigger>> {
+        let allowed_senders = serde_json::to_string(&request.allowed_senders)?;
+        let now = timestamp(Utc::now());
+        let sql = format!(
+            r#"UPDATE agent_triggers SET
+                   workspace_path = $1, prompt = $2, template = $3, harness = $4, provider = $5,
+                   model = $6, timeout_secs = $7, allowed_senders = $8, reply_webhook_url = $9,
+                   reply_email = $10, enabled = $11, updated_at = $12
+               WHERE user_id = $13 AND name = $14
+               RETURNING {TRIGGER_COLUMNS}"#
+        );
+        let row = on_pool!(&self.pool, |pool| sqlx::query_as::<_, TriggerRow>(&sql)
+            .bind(&request.workspace_path)
+            .bind(&request.prompt)
+            .bind(&request.template)
+            .bind(&request.harness)
+            .bind(&request.provider)
+            .bind(&request.model)
+            .bind(request.timeout_secs)
+            .bind(&allowed_senders)
+            .bind(&request.reply_webhook_url)
+            .bind(request.reply_email)
+            .bind(request.enabled)
+            .bind(&now)
+            .bind(user_id)
+            .bind(name)
+            .fetch_optional(pool)
+            .await)
+        .context("update trigger")?;
+        Ok(row.map(AgentTrigger::from))
+    }
+
+    /// Replace a trigger's token; the old one stops working.
+    pub async fn rotate_token(
+        &self,
+        user_id: &str,
+        name: &str,
+        token_hash: &str,
+        token_prefix: &str,
+    ) -> Result<Option<AgentTrigger>> {
+        let now = timestamp(Utc::now());
+        let sql = format!(
+            "UPDATE agent_triggers SET token_hash = $1, token_prefix = $2, updated_at = $3 \
+             WHERE user_id = $4 AND name = $5 RETURNING {TRIGGER_COLUMNS}"
+        );
+        let row = on_pool!(&self.pool, |pool| sqlx::query_as::<_, TriggerRow>(&sql)
+            .bind(token_hash)
+            .bind(token_prefix)
+            .bind(&now)
+            .bind(user_id)
+            .bind(name)
+            .fetch_optional(pool)
+            .await)
+        .context("rotate trigger token")?;
+        Ok(row.map(AgentTrigger::from))
+    }
+
+    pub async fn delete(&self, user_id: &str, name: &str) -> Result<bool> {
+        on_pool!(&self.pool, |pool| sqlx::query(
+            "DELETE FROM agent_triggers WHERE user_id = $1 AND name = $2"
+        )
+        .bind(user_id)
+        .bind(name)
+        .execute(pool)
+        .await
+        .map(|r| r.rows_affected() > 0))
+        .context("delete trigger")
+    }
+
+    /// Record a queued run of `trigger_id` for `event`.
+    pub async fn create_run(
+        &self,
+        trigger_id: &str,
+        source: TriggerSource,
+        event: &TriggerEvent,
+        session_id: &str,
+    ) -> Result<TriggerRun> {
+        let now = timestamp(Utc::now());
+        on_pool!(&self.pool, |pool| sqlx::query(
+            "UPDATE agent_triggers SET last_fired_at = $1 WHERE id = $2"
+        )
+        .bind(&now)
+        .bind(trigger_id)
+        .execute(pool)
+        .await
+        .map(|_| ()))
+        .context("mark trigger fired")?;
+        let sql = format!(
+            "INSERT INTO trigger_runs \
+                 (id, trigger_id, source, sender, subject, status, session_id, created_at) \
+             VALUES ($1, $2, $3, $4, $5, $6, $7, $8) RETURNING {RUN_COLUMNS}"
+        );
+        let id = format!("trun_{}", uuid::Uuid::new_v4().simple());
+        on_pool!(&self.pool, |pool| sqlx::query_as::<_, TriggerRun>(&sql)
+            .bind(&id)
+            .bind(trigger_id)
+            .bind(source)
+            .bind(&event.sender)
+            .bind(&event.subject)
+            .bind(TriggerRunStatus::Queued)
+            .bind(session_id)
+            .bind(&now)
+            .fetch_one(pool)
+            .await)
+        .context("create trigger run")
+    }
+
+    pub async fn mark_running(&self, run_id: &str) -> Result<()> {
+        let now = timestamp(Utc::now());
+        on_pool!(&self.pool, |pool| sqlx::query(
+            "UPDATE trigger_runs SET status = $1, started_at = $2 WHERE id = $3"
+        )
+        .bind(TriggerRunStatus::Running)
+        .bind(&now)
+        .bind(run_id)
+        .execute(pool)
+        .await
+        .map(|_| ()))
+        .context("mark trigger run running")
+    }
+
+    pub async fn finish_run(
+        &self,
+        run_id: &str,
+        status: TriggerRunStatus,
+        error: Option<&str>,
+        delivery: Option<&str>,
+    ) -> Result<()> {
+        let now = timestamp(Utc::now());
+        on_pool!(&self.pool, |pool| sqlx::query(
+            "UPDATE trigger_runs SET status = $1, error = $2, delivery = $3, finished_at = $4 \
+             WHERE id = $5"
+        )
+        .bind(status)
+        .bind(error)
+        .bind(delivery)
+        .bind(&now)
+        .bind(run_id)
+        .execute(pool)
+        .await
+        .map(|_| ()))
+        .context("finish trigger run")
+    }
+
+    /// Queued and running runs of a trigger.
+    pub async fn count_active_runs(&self, trigger_id: &str) -> Result<i64> {
+        on_pool!(&self.pool, |pool| sqlx::query_scalar(
+            "SELECT COUNT(*) FROM trigger_runs WHERE trigger_id = $1 AND status IN ($2, $3)"
+        )
+        .bind(trigger_id)
+        .bind(TriggerRunStatus::Queued)
+        .bind(TriggerRunStatus::Running)
+        .fetch_one(pool)
+        .await)
+        .context("count active trigger runs")
+    }
+
+    /// A trigger's runs, newest first.
+    pub async fn list_runs(&self, trigger_id: &str, limit: i64) -> Result<Vec<TriggerRun>> {
+        let sql = format!(
+            "SELECT {RUN_COLUMNS} FROM trigger_runs WHERE trigger_id = $1 \
+             ORDER BY created_at DESC LIMIT $2"
+        );
+        on_pool!(&self.pool, |pool| sqlx::query_as::<_, TriggerRun>(&sql)
+            .bind(trigger_id)
+            .bind(limit.clamp(1, MAX_RUNS_LIMIT))
+            .fetch_all(pool)
+            .await)
+        .context("list trigger runs")
+    }
+
+    /// Fail runs left queued or running by a previous process. With a shared
+    /// database this also catches runs of other replicas; they still record
+    /// their final status when they finish.
+    pub async fn fail_interrupted(&self) -> Result<u64> {
+        let now = timestamp(Utc::now());
+        on_pool!(&self.pool, |pool| sqlx::query(
+            "UPDATE trigger_runs SET status = $1, error = 'interrupted by a server restart', \
+                 finished_at = $2 \
+             WHERE status IN ($3, $4)"
+        )
+        .bind(TriggerRunStatus::Failed)
+        .bind(&now)
+        .bind(TriggerRunStatus::Queued)
+        .bind(TriggerRunStatus::Running)
+        .execute(pool)
+        .await
+        .map(|r| r.rows_affected()))
+        .context("fail interrupted trigger runs")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::Database;
+
+    async fn setup() -> (Database, TriggerRepository) {
+        let db = Database::in_memory().await.unwrap();
+        sqlx::query("INSERT INTO users (id, username, email, display_name) VALUES (?, ?, ?, ?)")
+            .bind("alice")
+            .bind("alice")
+            .bind("alice@example.com")
+            .bind("Alice")
+            .execute(db.pool())
+            .await
+            .unwrap();
+        let repo = TriggerRepository::new(db.shared().clone());
+        (db, repo)
+    }
+
+    fn request() -> SaveTriggerRequest {
+        SaveTriggerRequest {
+            name: None,
+            workspace_path: "/home/alice/inbox".to_string(),
+            prompt: "Handle this: {{body}}".to_string(),
+            template: None,
+            harness: None,
+            provider: None,
+            model: None,
+            timeout_secs: None,
+            allowed_senders: vec!["@example.com".to_string()],
+            reply_webhook_url: None,
+            reply_email: true,
+            enabled: true,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_trigger_lifecycle_and_runs() {
+        let (_db, repo) = setup().await;
+        let created = repo
+            .create("alice", "inbox", &request(), "hash1", "trg_abcd")
+            .await
+            .unwrap();
+        assert_eq!(created.allowed_senders, vec!["@example.com"]);
+        assert!(created.reply_email);
+
+        let found = repo.get_by_token_hash("hash1").await.unwrap().unwrap();
+        assert_eq!(found.id, created.id);
+
+        repo.rotate_token("alice", "inbox", "hash2", "trg_efgh")
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(repo.get_by_token_hash("hash1").await.unwrap().is_none());
+        assert!(repo.get_by_token_hash("hash2").await.unwrap().is_some());
+
+        let event = TriggerEvent {
+            sender: Some("bob@example.com".to_string()),
+            subject: Some("Invoice".to_string()),
+            ..TriggerEvent::default()
+        };
+        let run = repo
+            .create_run(&created.id, TriggerSource::Email, &event, "ses_1")
+            .await
+            .unwrap();
+        assert_eq!(run.status, TriggerRunStatus::Queued);
+        assert_eq!(repo.count_active_runs(&created.id).await.unwrap(), 1);
+
+        repo.mark_running(&run.id).await.unwrap();
+        repo.finish_run(&run.id, TriggerRunStatus::Succeeded, None, Some("sent"))
+            .await
+            .unwrap();
+        assert_eq!(repo.count_active_runs(&created.id).await.unwrap(), 0);
+        let runs = repo.list_runs(&created.id, 10).await.unwrap();
+        assert_eq!(runs[0].delivery.as_deref(), Some("sent"));
+        assert!(runs[0].finished_at.is_some());
+
+        repo.create_run(&created.id, TriggerSource::Webhook, &event, "ses_2")
+            .await
+            .unwrap();
+        assert_eq!(repo.fail_interrupted().await.unwrap(), 1);
+
+        assert!(repo.delete("alice", "inbox").await.unwrap());
+        assert!(repo.list_runs(&created.id, 10).await.unwrap().is_empty());
+    }
+}
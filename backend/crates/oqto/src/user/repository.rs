@@ -1,21 +1,21 @@
 //! User repository for database operations.
 
 use anyhow::{Context, Result};
-use sqlx::SqlitePool;
 use tracing::{debug, instrument};
 
 use super::models::{CreateUserRequest, UpdateUserRequest, User, UserListQuery, UserRole};
+use crate::db::{self, DbPool, on_pool};
 use crate::local::is_port_available;
 
 /// Repository for user database operations.
 #[derive(Debug, Clone)]
 pub struct UserRepository {
-    pool: SqlitePool,
+    pool: DbPool,
 }
 
 impl UserRepository {
     /// Create a new user repository.
-    pub fn new(pool: SqlitePool) -> Self {
+    pub fn new(pool: DbPool) -> Self {
         Self { pool }
     }
 
@@ -69,10 +69,10 @@ impl UserRepository {
 
         debug!("Creating user: {} ({})", request.username, id);
 
-        sqlx::query(
+        on_pool!(&self.pool, |pool| sqlx::query(
             r#"
             INSERT INTO users (id, external_id, username, email, password_hash, display_name, role, linux_username)
-            VALUES (?, ?, ?, ?, ?, ?, ?, ?)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
             "#,
         )
         .bind(id)
@@ -83,8 +83,9 @@ impl UserRepository {
         .bind(&display_name)
         .bind(role.to_string())
         .bind(&linux_username)
-        .execute(&self.pool)
+        .execute(pool)
         .await
+        .map(|r| r.rows_affected()))
         .context("Failed to insert user")?;
 
         self.get(id)
@@ -95,17 +96,17 @@ impl UserRepository {
     /// Get a user by ID.
     #[instrument(skip(self))]
     pub async fn get(&self, id: &str) -> Result<Option<User>> {
-        let user = sqlx::query_as::<_, User>(
+        let user = on_pool!(&self.pool, |pool| sqlx::query_as::<_, User>(
             r#"
             SELECT id, external_id, username, email, password_hash, display_name, 
                    avatar_url, role, is_active, created_at, updated_at, last_login_at, settings, mmry_port, sldr_port, linux_username, linux_uid
             FROM users
-            WHERE id = ?
+            WHERE id = $1
             "#,
         )
         .bind(id)
-        .fetch_optional(&self.pool)
-        .await
+        .fetch_optional(pool)
+        .await)
         .context("Failed to fetch user")?;
 
         Ok(user)
@@ -114,17 +115,17 @@ impl UserRepository {
     /// Get a user by username.
     #[instrument(skip(self))]
     pub async fn get_by_username(&self, username: &str) -> Result<Option<User>> {
-        let user = sqlx::query_as::<_, User>(
+        let user = on_pool!(&self.pool, |pool| sqlx::query_as::<_, User>(
             r#"
             SELECT id, external_id, username, email, password_hash, display_name,
                    avatar_url, role, is_active, created_at, updated_at, last_login_at, settings, mmry_port, sldr_port, linux_username, linux_uid
             FROM users
-            WHERE username = ?
+            WHERE username = $1
             "#,
         )
         .bind(username)
-        .fetch_optional(&self.pool)
-        .await
+        .fetch_optional(pool)
+        .await)
         .context("Failed to fetch user by username")?;
 
         Ok(user)
@@ -133,17 +134,17 @@ impl UserRepository {
     /// Get a user by email.
     #[instrument(skip(self))]
     pub async fn get_by_email(&self, email: &str) -> Result<Option<User>> {
-        let user = sqlx::query_as::<_, User>(
+        let user = on_pool!(&self.pool, |pool| sqlx::query_as::<_, User>(
             r#"
             SELECT id, external_id, username, email, password_hash, display_name,
                    avatar_url, role, is_active, created_at, updated_at, last_login_at, settings, mmry_port, sldr_port, linux_username, linux_uid
             FROM users
-            WHERE email = ?
+            WHERE email = $1
             "#,
         )
         .bind(email)
-        .fetch_optional(&self.pool)
-        .await
+        .fetch_optional(pool)
+        .await)
         .context("Failed to fetch user by email")?;
 
         Ok(user)
@@ -152,17 +153,17 @@ impl UserRepository {
     /// Get a user by external ID (OIDC subject).
     #[instrument(skip(self))]
     pub async fn get_by_external_id(&self, external_id: &str) -> Result<Option<User>> {
-        let user = sqlx::query_as::<_, User>(
+        let user = on_pool!(&self.pool, |pool| sqlx::query_as::<_, User>(
             r#"
             SELECT id, external_id, username, email, password_hash, display_name,
                    avatar_url, role, is_active, created_at, updated_at, last_login_at, settings, mmry_port, sldr_port, linux_username, linux_uid
             FROM users
-            WHERE external_id = ?
+            WHERE external_id = $1
             "#,
         )
         .bind(external_id)
-        .fetch_optional(&self.pool)
-        .await
+        .fetch_optional(pool)
+        .await)
         .context("Failed to fetch user by external_id")?;
 
         Ok(user)
@@ -174,51 +175,46 @@ impl UserRepository {
         let limit = query.limit.unwrap_or(100);
         let offset = query.offset.unwrap_or(0);
 
-        // Build dynamic query based on filters
-        let mut sql = String::from(
-            r#"
-            SELECT id, external_id, username, email, password_hash, display_name,
-                   avatar_url, role, is_active, created_at, updated_at, last_login_at, settings, mmry_port, sldr_port, linux_username, linux_uid
-            FROM users
-            WHERE 1=1
-            "#,
-        );
-
-        let mut bind_values: Vec<String> = Vec::new();
-
-        if let Some(role) = &query.role {
-            sql.push_str(" AND role = ?");
-            bind_values.push(role.to_string());
-        }
-
-        if let Some(is_active) = query.is_active {
-            sql.push_str(" AND is_active = ?");
-            bind_values.push(if is_active { "1" } else { "0" }.to_string());
-        }
-
-        if let Some(search) = &query.search {
-            sql.push_str(" AND (username LIKE ? OR email LIKE ? OR display_name LIKE ?)");
-            let pattern = format!("%{}%", search);
-            bind_values.push(pattern.clone());
-            bind_values.push(pattern.clone());
-            bind_values.push(pattern);
-        }
-
-        sql.push_str(" ORDER BY created_at DESC LIMIT ? OFFSET ?");
+        let users = on_pool!(&self.pool, |pool| {
+            // Build dynamic query based on filters
+            let mut qb = db::query_builder(
+                pool,
+                r#"
+                SELECT id, external_id, username, email, password_hash, display_name,
+                       avatar_url, role, is_active, created_at, updated_at, last_login_at, settings, mmry_port, sldr_port, linux_username, linux_uid
+                FROM users
+                WHERE 1=1
+                "#,
+            );
+
+            if let Some(role) = &query.role {
+                qb.push(" AND role = ").push_bind(role.to_string());
+            }
 
-        // Execute with dynamic bindings
-        let mut query_builder = sqlx::query_as::<_, User>(&sql);
+            if let Some(is_active) = query.is_active {
+                qb.push(" AND is_active = ").push_bind(is_active);
+            }
 
-        for value in &bind_values {
-            query_builder = query_builder.bind(value);
-        }
+            if let Some(search) = &query.search {
+                // LOWER() keeps the search case-insensitive on Postgres too.
+                let pattern = format!("%{}%", search.to_lowercase());
+                qb.push(" AND (LOWER(username) LIKE ")
+                    .push_bind(pattern.clone())
+                    .push(" OR LOWER(email) LIKE ")
+                    .push_bind(pattern.clone())
+                    .push(" OR LOWER(display_name) LIKE ")
+                    .push_bind(pattern)
+                    .push(")");
+            }
 
-        query_builder = query_builder.bind(limit).bind(offset);
+            qb.push(" ORDER BY created_at DESC LIMIT ")
+                .push_bind(limit)
+                .push(" OFFSET ")
+                .push_bind(offset);
 
-        let users = query_builder
-            .fetch_all(&self.pool)
-            .await
-            .context("Failed to list users")?;
+            qb.build_query_as::<User>().fetch_all(pool).await
+        })
+        .context("Failed to list users")?;
 
         Ok(users)
     }
@@ -232,78 +228,70 @@ impl UserRepository {
             .await?
             .ok_or_else(|| anyhow::anyhow!("User not found: {}", id))?;
 
-        // Build update query dynamically
-        let mut updates = Vec::new();
-        let mut values: Vec<String> = Vec::new();
-
-        if let Some(username) = &request.username {
-            updates.push("username = ?");
-            values.push(username.clone());
-        }
-
-        if let Some(email) = &request.email {
-            updates.push("email = ?");
-            values.push(email.clone());
+        let has_updates = request.username.is_some()
+            || request.email.is_some()
+            || request.password.is_some()
+            || request.display_name.is_some()
+            || request.avatar_url.is_some()
+            || request.role.is_some()
+            || request.is_active.is_some()
+            || request.settings.is_some()
+            || request.linux_username.is_some()
+            || request.linux_uid.is_some();
+        if !has_updates {
+            return Ok(existing);
         }
 
-        if let Some(password) = &request.password {
-            updates.push("password_hash = ?");
-            values.push(password.clone());
-        }
+        on_pool!(&self.pool, |pool| {
+            // Build update query dynamically
+            let mut qb = db::query_builder(pool, "UPDATE users SET updated_at = ");
+            qb.push_bind(db::now());
 
-        if let Some(display_name) = &request.display_name {
-            updates.push("display_name = ?");
-            values.push(display_name.clone());
-        }
-
-        if let Some(avatar_url) = &request.avatar_url {
-            updates.push("avatar_url = ?");
-            values.push(avatar_url.clone());
-        }
+            if let Some(username) = &request.username {
+                qb.push(", username = ").push_bind(username.clone());
+            }
 
-        if let Some(role) = &request.role {
-            updates.push("role = ?");
-            values.push(role.to_string());
-        }
+            if let Some(email) = &request.email {
+                qb.push(", email = ").push_bind(email.clone());
+            }
 
-        if let Some(is_active) = request.is_active {
-            updates.push("is_active = ?");
-            values.push(if is_active { "1" } else { "0" }.to_string());
-        }
+            if let Some(password) = &request.password {
+                qb.push(", password_hash = ").push_bind(password.clone());
+            }
 
-        if let Some(settings) = &request.settings {
-            updates.push("settings = ?");
-            values.push(settings.clone());
-        }
+            if let Some(display_name) = &request.display_name {
+                qb.push(", display_name = ").push_bind(display_name.clone());
+            }
 
-        if let Some(linux_username) = &request.linux_username {
-            updates.push("linux_username = ?");
-            values.push(linux_username.clone());
-        }
+            if let Some(avatar_url) = &request.avatar_url {
+                qb.push(", avatar_url = ").push_bind(avatar_url.clone());
+            }
 
-        if let Some(linux_uid) = request.linux_uid {
-            updates.push("linux_uid = ?");
-            values.push(linux_uid.to_string());
-        }
+            if let Some(role) = &request.role {
+                qb.push(", role = ").push_bind(role.to_string());
+            }
 
-        if updates.is_empty() {
-            return Ok(existing);
-        }
+            if let Some(is_active) = request.is_active {
+                qb.push(", is_active = ").push_bind(is_active);
+            }
 
-        updates.push("updated_at = datetime('now')");
+            if let Some(settings) = &request.settings {
+                qb.push(", settings = ").push_bind(settings.clone());
+            }
 
-        let sql = format!("UPDATE users SET {} WHERE id = ?", updates.join(", "));
+            if let Some(linux_username) = &request.linux_username {
+                qb.push(", linux_username = ")
+                    .push_bind(linux_username.clone());
+            }
 
-        let mut query_builder = sqlx::query(&sql);
-        for value in &values {
-            query_builder = query_builder.bind(value);
-        }
-        query_builder = query_builder.bind(id);
+            if let Some(linux_uid) = request.linux_uid {
+                qb.push(", linux_uid = ").push_bind(linux_uid);
+            }
 
-        query_builder
-            .execute(&self.pool)
-            .await
-            .context("Failed to update user")?;
+            qb.push(" WHERE id = ").push_bind(id.to_string());
+            qb.build().execute(pool).await.map(|r| r.rows_affected())
+        })
+        .context("Failed to update user")?;
 
         self.get(id)
             .await?
@@ -313,38 +301,63 @@ impl UserRepository {
     /// Delete a user.
     #[instrument(skip(self))]
     pub async fn delete(&self, id: &str) -> Result<()> {
-        let result = sqlx::query("DELETE FROM users WHERE id = ?")
-            .bind(id)
-            .execute(&self.pool)
-            .await
-            .context("Failed to delete user")?;
+        let deleted = on_pool!(&self.pool, |pool| sqlx::query(
+            "DELETE FROM users WHERE id = $1"
+        )
+        .bind(id)
+        .execute(pool)
+        .await
+        .map(|r| r.rows_affected()))
+        .context("Failed to delete user")?;
 
-        if result.rows_affected() == 0 {
+        if deleted == 0 {
             return Err(anyhow::anyhow!("User not found: {}", id));
         }
 
         Ok(())
     }
 
+    /// Link a user to an external identity (OIDC subject).
+    #[instrument(skip(self))]
+    pub async fn set_external_id(&self, id: &str, external_id: &str) -> Result<()> {
+        on_pool!(&self.pool, |pool| sqlx::query(
+            "UPDATE users SET external_id = $1, updated_at = $2 WHERE id = $3"
+        )
+        .bind(external_id)
+        .bind(db::now())
+        .bind(id)
+        .execute(pool)
+        .await
+        .map(|r| r.rows_affected()))
+        .context("Failed to set external_id")?;
+
+        Ok(())
+    }
+
     /// Update last login timestamp.
     #[instrument(skip(self))]
     pub async fn update_last_login(&self, id: &str) -> Result<()> {
-        sqlx::query("UPDATE users SET last_login_at = datetime('now') WHERE id = ?")
-            .bind(id)
-            .execute(&self.pool)
-            .await
-            .context("Failed to update last login")?;
+        on_pool!(&self.pool, |pool| sqlx::query(
+            "UPDATE users SET last_login_at = $1 WHERE id = $2"
+        )
+        .bind(db::now())
+        .bind(id)
+        .execute(pool)
+        .await
+        .map(|r| r.rows_affected()))
+        .context("Failed to update last login")?;
 
         Ok(())
     }
 
     pub async fn get_mmry_port(&self, user_id: &str) -> Result<Option<i64>> {
-        let row: Option<(Option<i64>,)> =
-            sqlx::query_as("SELECT mmry_port FROM users WHERE id = ?")
-                .bind(user_id)
-                .fetch_optional(&self.pool)
-                .await
-                .context("Failed to fetch user mmry_port")?;
+        let row: Option<(Option<i64>,)> = on_pool!(&self.pool, |pool| sqlx::query_as(
+            "SELECT mmry_port FROM users WHERE id = $1"
+        )
+        .bind(user_id)
+        .fetch_optional(pool)
+        .await)
+        .context("Failed to fetch user mmry_port")?;
         Ok(row.and_then(|r| r.0))
     }
 
@@ -353,22 +366,25 @@ impl UserRepository {
         // provisioned in the DB. Ensure the user row exists so we can persist mmry_port.
         //
         // This uses conservative defaults; auth/role decisions come from the auth layer.
-        let exists: Option<(i64,)> = sqlx::query_as("SELECT 1 FROM users WHERE id = ? LIMIT 1")
-            .bind(user_id)
-            .fetch_optional(&self.pool)
-            .await
-            .context("checking if user exists")?;
+        let exists: Option<(String,)> = on_pool!(&self.pool, |pool| sqlx::query_as(
+            "SELECT id FROM users WHERE id = $1 LIMIT 1"
+        )
+        .bind(user_id)
+        .fetch_optional(pool)
+        .await)
+        .context("checking if user exists")?;
         if exists.is_none() {
             let email = format!("{}@localhost", user_id);
-            sqlx::query(
-                "INSERT OR IGNORE INTO users (id, username, email, display_name, role) VALUES (?, ?, ?, ?, 'user')",
+            on_pool!(&self.pool, |pool| sqlx::query(
+                "INSERT INTO users (id, username, email, display_name, role) VALUES ($1, $2, $3, $4, 'user') ON CONFLICT DO NOTHING",
             )
             .bind(user_id)
             .bind(user_id)
             .bind(&email)
             .bind(user_id)
-            .execute(&self.pool)
+            .execute(pool)
             .await
+            .map(|r| r.rows_affected()))
             .context("creating placeholder user")?;
         }
 
@@ -383,11 +399,12 @@ impl UserRepository {
         // Try a few times in case of concurrent allocations.
         for _ in 0..10 {
             // Fetch current allocations.
-            let used_rows: Vec<(i64,)> =
-                sqlx::query_as("SELECT mmry_port FROM users WHERE mmry_port IS NOT NULL")
-                    .fetch_all(&self.pool)
-                    .await
-                    .context("Failed to list allocated mmry ports")?;
+            let used_rows: Vec<(i64,)> = on_pool!(&self.pool, |pool| sqlx::query_as(
+                "SELECT mmry_port FROM users WHERE mmry_port IS NOT NULL"
+            )
+            .fetch_all(pool)
+            .await)
+            .context("Failed to list allocated mmry ports")?;
             let used: std::collections::HashSet<i64> = used_rows.into_iter().map(|r| r.0).collect();
 
             // Pick first free port in range that is also bindable.
@@ -411,17 +428,21 @@ impl UserRepository {
                 );
             };
 
-            let res = sqlx::query(
-                "UPDATE users SET mmry_port = ?, updated_at = datetime('now') WHERE id = ? AND mmry_port IS NULL",
+            let res = on_pool!(&self.pool, |pool| {
+                sqlx::query(
+                "UPDATE users SET mmry_port = $1, updated_at = $2 WHERE id = $3 AND mmry_port IS NULL",
             )
             .bind(port)
+            .bind(db::now())
             .bind(user_id)
-            .execute(&self.pool)
-            .await;
+            .execute(pool)
+            .await
+            .map(|r| r.rows_affected())
+            });
 
             match res {
-                Ok(r) => {
-                    if r.rows_affected() == 0 {
+                Ok(updated) => {
+                    if updated == 0 {
                         // Someone else set it.
                         if let Some(p) = self.get_mmry_port(user_id).await? {
                             return Ok(p);
@@ -432,7 +453,7 @@ impl UserRepository {
                 }
                 Err(e) => {
                     // Likely unique constraint race; retry.
-                    if e.to_string().contains("UNIQUE") {
+                    if matches!(&e, sqlx::Error::Database(d) if d.is_unique_violation()) {
                         continue;
                     }
                     return Err(e).context("allocating user mmry port");
@@ -444,32 +465,36 @@ impl UserRepository {
     }
 
     pub async fn get_sldr_port(&self, user_id: &str) -> Result<Option<i64>> {
-        let row: Option<(Option<i64>,)> =
-            sqlx::query_as("SELECT sldr_port FROM users WHERE id = ?")
-                .bind(user_id)
-                .fetch_optional(&self.pool)
-                .await
-                .context("Failed to fetch user sldr_port")?;
+        let row: Option<(Option<i64>,)> = on_pool!(&self.pool, |pool| sqlx::query_as(
+            "SELECT sldr_port FROM users WHERE id = $1"
+        )
+        .bind(user_id)
+        .fetch_optional(pool)
+        .await)
+        .context("Failed to fetch user sldr_port")?;
         Ok(row.and_then(|r| r.0))
     }
 
     pub async fn ensure_sldr_port(&self, user_id: &str, base_port: u16, range: u16) -> Result<i64> {
-        let exists: Option<(i64,)> = sqlx::query_as("SELECT 1 FROM users WHERE id = ? LIMIT 1")
-            .bind(user_id)
-            .fetch_optional(&self.pool)
-            .await
-            .context("checking if user exists")?;
+        let exists: Option<(String,)> = on_pool!(&self.pool, |pool| sqlx::query_as(
+            "SELECT id FROM users WHERE id = $1 LIMIT 1"
+        )
+        .bind(user_id)
+        .fetch_optional(pool)
+        .await)
+        .context("checking if user exists")?;
         if exists.is_none() {
             let email = format!("{}@localhost", user_id);
-            sqlx::query(
-                "INSERT OR IGNORE INTO users (id, username, email, display_name, role) VALUES (?, ?, ?, ?, 'user')",
+            on_pool!(&self.pool, |pool| sqlx::query(
+                "INSERT INTO users (id, username, email, display_name, role) VALUES ($1, $2, $3, $4, 'user') ON CONFLICT DO NOTHING",
             )
             .bind(user_id)
             .bind(user_id)
             .bind(&email)
             .bind(user_id)
-            .execute(&self.pool)
+            .execute(pool)
             .await
+            .map(|r| r.rows_affected()))
             .context("creating placeholder user")?;
         }
 
@@ -482,11 +507,12 @@ impl UserRepository {
         }
 
         for _ in 0..10 {
-            let used_rows: Vec<(i64,)> =
-                sqlx::query_as("SELECT sldr_port FROM users WHERE sldr_port IS NOT NULL")
-                    .fetch_all(&self.pool)
-                    .await
-                    .context("Failed to list allocated sldr ports")?;
+            let used_rows: Vec<(i64,)> = on_pool!(&self.pool, |pool| sqlx::query_as(
+                "SELECT sldr_port FROM users WHERE sldr_port IS NOT NULL"
+            )
+            .fetch_all(pool)
+            .await)
+            .context("Failed to list allocated sldr ports")?;
             let used: std::collections::HashSet<i64> = used_rows.into_iter().map(|r| r.0).collect();
 
             let mut candidate: Option<i64> = None;
@@ -509,17 +535,21 @@ impl UserRepository {
                 );
             };
 
-            let res = sqlx::query(
-                "UPDATE users SET sldr_port = ?, updated_at = datetime('now') WHERE id = ? AND sldr_port IS NULL",
+            let res = on_pool!(&self.pool, |pool| {
+                sqlx::query(
+                "UPDATE users SET sldr_port = $1, updated_at = $2 WHERE id = $3 AND sldr_port IS NULL",
             )
             .bind(port)
+            .bind(db::now())
             .bind(user_id)
-            .execute(&self.pool)
-            .await;
+            .execute(pool)
+            .await
+            .map(|r| r.rows_affected())
+            });
 
             match res {
-                Ok(r) => {
-                    if r.rows_affected() == 0 {
+                Ok(updated) => {
+                    if updated == 0 {
                         if let Some(p) = self.get_sldr_port(user_id).await? {
                             return Ok(p);
                         }
@@ -528,7 +558,7 @@ impl UserRepository {
                     }
                 }
                 Err(e) => {
-                    if e.to_string().contains("UNIQUE") {
+                    if matches!(&e, sqlx::Error::Database(d) if d.is_unique_violation()) {
                         continue;
                     }
                     return Err(e).context("allocating user sldr port");
@@ -542,11 +572,13 @@ impl UserRepository {
     /// Check if a username is available.
     #[instrument(skip(self))]
     pub async fn is_username_available(&self, username: &str) -> Result<bool> {
-        let count: (i64,) = sqlx::query_as("SELECT COUNT(*) FROM users WHERE username = ?")
-            .bind(username)
-            .fetch_one(&self.pool)
-            .await
-            .context("Failed to check username availability")?;
+        let count: (i64,) = on_pool!(&self.pool, |pool| sqlx::query_as(
+            "SELECT COUNT(*) FROM users WHERE username = $1"
+        )
+        .bind(username)
+        .fetch_one(pool)
+        .await)
+        .context("Failed to check username availability")?;
 
         Ok(count.0 == 0)
     }
@@ -554,11 +586,13 @@ impl UserRepository {
     /// Check if an email is available.
     #[instrument(skip(self))]
     pub async fn is_email_available(&self, email: &str) -> Result<bool> {
-        let count: (i64,) = sqlx::query_as("SELECT COUNT(*) FROM users WHERE email = ?")
-            .bind(email)
-            .fetch_one(&self.pool)
-            .await
-            .context("Failed to check email availability")?;
+        let count: (i64,) = on_pool!(&self.pool, |pool| sqlx::query_as(
+            "SELECT COUNT(*) FROM users WHERE email = $1"
+        )
+        .bind(email)
+        .fetch_one(pool)
+        .await)
+        .context("Failed to check email availability")?;
 
         Ok(count.0 == 0)
     }
@@ -566,10 +600,12 @@ impl UserRepository {
     /// Count total users.
     #[instrument(skip(self))]
     pub async fn count(&self) -> Result<i64> {
-        let count: (i64,) = sqlx::query_as("SELECT COUNT(*) FROM users")
-            .fetch_one(&self.pool)
-            .await
-            .context("Failed to count users")?;
+        let count: (i64,) = on_pool!(&self.pool, |pool| sqlx::query_as(
+            "SELECT COUNT(*) FROM users"
+        )
+        .fetch_one(pool)
+        .await)
+        .context("Failed to count users")?;
 
         Ok(count.0)
     }
@@ -577,11 +613,13 @@ impl UserRepository {
     /// Count users by role.
     #[instrument(skip(self))]
     pub async fn count_by_role(&self, role: UserRole) -> Result<i64> {
-        let count: (i64,) = sqlx::query_as("SELECT COUNT(*) FROM users WHERE role = ?")
-            .bind(role.to_string())
-            .fetch_one(&self.pool)
-            .await
-            .context("Failed to count users by role")?;
+        let count: (i64,) = on_pool!(&self.pool, |pool| sqlx::query_as(
+            "SELECT COUNT(*) FROM users WHERE role = $1"
+        )
+        .bind(role.to_string())
+        .fetch_one(pool)
+        .await)
+        .context("Failed to count users by role")?;
 
         Ok(count.0)
     }
@@ -591,8 +629,8 @@ impl UserRepository {
 mod tests {
     use super::*;
 
-    async fn setup_test_db() -> SqlitePool {
-        let pool = SqlitePool::connect(":memory:").await.unwrap();
+    async fn setup_test_db() -> DbPool {
+        let pool = sqlx::SqlitePool::connect(":memory:").await.unwrap();
 
         // Run migrations
         sqlx::query(
@@ -633,7 +671,7 @@ mod tests {
         .await
         .unwrap();
 
-        pool
+        DbPool::Sqlite(pool)
     }
 
     #[tokio::test]
@@ -226,35 +226,55 @@ impl UserService {
         }
     }
 
-    /// Get or create a user from OIDC claims.
+    /// Record a sign-in that did not go through [`Self::verify_credentials`].
+    #[instrument(skip(self))]
+    pub async fn record_login(&self, id: &str) -> Result<()> {
+        self.repo.update_last_login(id).await
+    }
+
+    /// Find or create the user behind an OIDC identity.
+    ///
+    /// Users are matched by `external_id` (the provider's subject). An
+    /// account with the same email is linked to the identity only when the
+    /// provider vouches for the email; otherwise anyone able to set that
+    /// email at the provider could take it over. Returns None when nothing
+    /// matches and `provision` is false.
     #[instrument(skip(self))]
     pub async fn get_or_create_from_oidc(
         &self,
         external_id: &str,
         email: &str,
+        email_verified: bool,
         name: &str,
-    ) -> Result<User> {
-        // Try to find existing user by external_id
+        provision: bool,
+    ) -> Result<Option<User>> {
         if let Some(user) = self.repo.get_by_external_id(external_id).await? {
-            // Update last login
             self.repo.update_last_login(&user.id).await?;
-            return Ok(user);
+            return Ok(Some(user));
         }
 
-        // Try to find by email and link
         if let Some(existing) = self.repo.get_by_email(email).await? {
-            // Link the external_id to existing user
-            let update = UpdateUserRequest {
-                display_name: Some(name.to_string()),
-                ..Default::default()
-            };
-            // Note: We'd need to add external_id to UpdateUserRequest to do this properly
-            let user = self.repo.update(&existing.id, update).await?;
-            return Ok(user);
+            if !email_verified || existing.external_id.is_some() {
+                bail!("An account with this email already exists and cannot be linked");
+            }
+            self.repo.set_external_id(&existing.id, external_id).await?;
+            self.repo.update_last_login(&existing.id).await?;
+            let user = self
+                .repo
+                .get(&existing.id)
+                .await?
+                .ok_or_else(|| anyhow::anyhow!("User not found after linking"))?;
+            return Ok(Some(user));
         }
 
-        // Create new user
-        let username = generate_username_from_email(email);
+        if !provision {
+            return Ok(None);
+        }
+
+        let mut username = generate_username_from_email(email);
+        if self.repo.get_by_username(&username).await?.is_some() {
+            username = format!("{}_{}", username, nanoid::nanoid!(6));
+        }
         let request = CreateUserRequest {
             username,
             email: email.to_string(),
@@ -264,7 +284,9 @@ impl UserService {
             external_id: Some(external_id.to_string()),
         };
 
-        self.repo.create(request).await
+        let user = self.repo.create(request).await?;
+        self.repo.update_last_login(&user.id).await?;
+        Ok(Some(user))
     }
 
     /// Get user statistics.
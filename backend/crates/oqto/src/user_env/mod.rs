@@ -0,0 +1,634 @@
+//! Moving a user's environment between servers.
+//!
+//! An environment archive is a zip with `environment.json`
+//! ([`EnvironmentManifest`]) at its root: the user's settings, saved macros
+//! and the workspaces under their workspace root with metadata and mmry
+//! memories. With data, each workspace's files follow under
+//! `workspaces/<name>/`, minus what its `.oqtoignore` and `[user_env]
+//! data_exclude` leave out. Importing applies an archive to a user, usually
+//! on another server (a personal install moving to a team server), and
+//! resolves clashes with existing settings keys, macro names and workspace
+//! directories by the chosen [`ConflictPolicy`].
+
+mod models;
+
+pub use models::{
+    ConflictPolicy, EnvironmentManifest, EnvironmentSource, ExportQuery, ImportAction, ImportQuery,
+    ImportReport, ImportedItem, MemoryRecord, SettingsOutcome, UserEnvConfig, WorkspaceEntry,
+};
+
+use std::collections::HashSet;
+use std::io::{Cursor, Read, Seek, SeekFrom, Write};
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Component, Path, PathBuf};
+
+use anyhow::{Context, Result, anyhow, bail};
+use axum::body::Bytes;
+use mmry_core::agent_ctx::AgentCtx;
+use mmry_core::memory::MemoryType;
+use mmry_core::memory_file::{MemoryEvent, MemoryFile};
+use oqto_files::archive::ExclusionRules;
+use serde_json::{Map, Value};
+use tracing::debug;
+use zip::write::SimpleFileOptions;
+use zip::{ZipArchive, ZipWriter};
+
+use crate::workspace::meta::{load_workspace_meta, write_workspace_meta};
+
+/// Name of the manifest at the root of every archive.
+pub const MANIFEST_NAME: &str = "environment.json";
+
+/// Archive format written by this server. Older formats are read as well.
+pub const FORMAT_VERSION: u32 = 1;
+
+/// Directory of workspace files in an archive.
+const DATA_DIR: &str = "workspaces";
+
+/// Whether `name` can be a directory directly under a workspace root:
+/// one plain path component, not hidden.
+pub fn valid_workspace_name(name: &str) -> bool {
+    let mut components = Path::new(name).components();
+    matches!(components.next(), Some(Component::Normal(_)))
+        && components.next().is_none()
+        && !name.starts_with('.')
+        && !name.contains('/')
+}
+
+/// Workspaces under `root`: its directories that are not hidden, sorted.
+pub fn list_workspaces(root: &Path) -> Result<Vec<String>> {
+    if !root.exists() {
+        return Ok(Vec::new());
+    }
+    let mut names = Vec::new();
+    for entry in std::fs::read_dir(root).with_context(|| format!("reading {}", root.display()))? {
+        let entry = entry?;
+        if !entry.file_type()?.is_dir() {
+            continue;
+        }
+        if let Some(name) = entry.file_name().to_str()
+            && valid_workspace_name(name)
+        {
+            names.push(name.to_string());
+        }
+    }
+    names.sort();
+    Ok(names)
+}
+
+/// Describe the workspaces under `root` for an archive. With `include_data`
+/// the entries also count the files that would be carried.
+pub fn collect_workspaces(
+    root: &Path,
+    include_data: bool,
+    config: &UserEnvConfig,
+) -> Result<Vec<WorkspaceEntry>> {
+    let mut entries = Vec::new();
+    for name in list_workspaces(root)? {
+        let dir = root.join(&name);
+        let memories = read_memories(&dir).unwrap_or_else(|e| {
+            debug!(workspace = %dir.display(), "No memories exported: {e:#}");
+            Vec::new()
+        });
+        let (files, bytes) = if include_data {
+            data_files(&dir, config)
+                .iter()
+                .fold((0, 0), |(files, bytes), file| {
+                    (files + 1, bytes + file.size)
+                })
+        } else {
+            (0, 0)
+        };
+        entries.push(WorkspaceEntry {
+            meta: load_workspace_meta(&dir),
+            name,
+            memories,
+            data: include_data,
+            files,
+            bytes,
+        });
+    }
+    Ok(entries)
+}
+
+/// A workspace file to archive.
+struct DataFile {
+    path: PathBuf,
+    /// `/`-separated path from the workspace directory.
+    relative: String,
+    size: u64,
+    mode: u32,
+}
+
+/// Regular files of a workspace, minus excluded ones. Symlinks are not
+/// followed, so nothing outside the workspace is carried.
+fn data_files(dir: &Path, config: &UserEnvConfig) -> Vec<DataFile> {
+    let rules = ExclusionRules::load(dir, &config.data_exclude);
+    rules
+        .walk(dir, dir, |_, _| {})
+        .filter(|entry| entry.file_type().is_file())
+        .filter_map(|entry| {
+            let metadata = entry.metadata().ok()?;
+            let relative = entry
+                .path()
+                .strip_prefix(dir)
+                .ok()?
+                .components()
+                .map(|c| c.as_os_str().to_string_lossy())
+                .collect::<Vec<_>>()
+                .join("/");
+            Some(DataFile {
+                path: entry.path().to_path_buf(),
+                relative,
+                size: metadata.len(),
+                mode: metadata.permissions().mode() & 0o777,
+            })
+        })
+        .collect()
+}
+
+/// Write an archive of `manifest` to a temporary file, with the files of
+/// the workspaces marked `data` read from `root`. Returns the file, rewound,
+/// and its size.
+pub fn write_archive(
+    manifest: &EnvironmentManifest,
+    root: &Path,
+    config: &UserEnvConfig,
+) -> Result<(std::fs::File, u64)> {
+    let mut zip = ZipWriter::new(tempfile::tempfile().context("creating archive file")?);
+    let options = SimpleFileOptions::default().compression_method(zip::CompressionMethod::Deflated);
+
+    zip.start_file(MANIFEST_NAME, options.unix_permissions(0o644))?;
+    zip.write_all(&serde_json::to_vec_pretty(manifest)?)?;
+
+    for workspace in manifest.workspaces.iter().filter(|w| w.data) {
+        let dir = root.join(&workspace.name);
+        for file in data_files(&dir, config) {
+            // Files can go away while the archive is written.
+            let mut input = match std::fs::File::open(&file.path) {
+                Ok(input) => input,
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => continue,
+                Err(e) => {
+                    return Err(e).with_context(|| format!("reading {}", file.path.display()));
+                }
+            };
+            zip.start_file(
+                format!("{DATA_DIR}/{}/{}", workspace.name, file.relative),
+                options.unix_permissions(file.mode),
+            )?;
+            std::io::copy(&mut input, &mut zip)?;
+        }
+    }
+
+    let mut file = zip.finish()?;
+    file.flush()?;
+    let size = file.seek(SeekFrom::End(0))?;
+    file.seek(SeekFrom::Start(0))?;
+    Ok((file, size))
+}
+
+/// An uploaded archive.
+pub struct EnvironmentArchive {
+    manifest: EnvironmentManifest,
+    zip: ZipArchive<Cursor<Bytes>>,
+    /// Workspace file bytes that may still be extracted.
+    remaining: u64,
+    max_data_bytes: u64,
+}
+
+impl EnvironmentArchive {
+    /// Read and check the manifest of an archive.
+    pub fn open(bytes: Bytes, config: &UserEnvConfig) -> Result<Self> {
+        let mut zip = ZipArchive::new(Cursor::new(bytes)).context("not a zip archive")?;
+        let manifest: EnvironmentManifest = {
+            let file = zip
+                .by_name(MANIFEST_NAME)
+                .map_err(|_| anyhow!("archive has no {MANIFEST_NAME}"))?;
+            serde_json::from_reader(file).with_context(|| format!("parsing {MANIFEST_NAME}"))?
+        };
+        if manifest.version > FORMAT_VERSION {
+            bail!(
+                "archive format {} is newer than this server supports ({FORMAT_VERSION})",
+                manifest.version
+            );
+        }
+        if let Some(workspace) = manifest
+            .workspaces
+            .iter()
+            .find(|w| !valid_workspace_name(&w.name))
+        {
+            bail!("invalid workspace name {:?}", workspace.name);
+        }
+        // Declared sizes, checked again while extracting.
+        let declared: u64 = (0..zip.len())
+            .filter_map(|i| {
+                let file = zip.by_index_raw(i).ok()?;
+                file.name().starts_with(DATA_DIR).then(|| file.size())
+            })
+            .sum();
+        if declared > config.max_data_bytes {
+            bail!(
+                "archive holds {declared} bytes, more than the {} allowed",
+                config.max_data_bytes
+            );
+        }
+        Ok(Self {
+            manifest,
+            zip,
+            remaining: config.max_data_bytes,
+            max_data_bytes: config.max_data_bytes,
+        })
+    }
+
+    pub fn manifest(&self) -> &EnvironmentManifest {
+        &self.manifest
+    }
+
+    /// Extract the files of workspace `name` into `dest`, overwriting
+    /// existing files. Entries with unsafe paths are skipped. Returns the
+    /// number of files written.
+    pub fn extract_workspace(&mut self, name: &str, dest: &Path) -> Result<u64> {
+        let prefix = Path::new(DATA_DIR).join(name);
+        let mut files = 0;
+        for i in 0..self.zip.len() {
+            let mut file = self.zip.by_index(i)?;
+            let Some(path) = file.enclosed_name() else {
+                continue;
+            };
+            let Ok(relative) = path.strip_prefix(&prefix) else {
+                continue;
+            };
+            if relative.as_os_str().is_empty() {
+                continue;
+            }
+            let out_path = dest.join(relative);
+            if file.is_dir() {
+                std::fs::create_dir_all(&out_path)
+                    .with_context(|| format!("creating {}", out_path.display()))?;
+                continue;
+            }
+            if let Some(parent) = out_path.parent() {
+                std::fs::create_dir_all(parent)
+                    .with_context(|| format!("creating {}", parent.display()))?;
+            }
+            let mut out = std::fs::File::create(&out_path)
+                .with_context(|| format!("writing {}", out_path.display()))?;
+            let written = std::io::copy(&mut (&mut file).take(self.remaining + 1), &mut out)?;
+            if written > self.remaining {
+                bail!(
+                    "archive data exceeds the {} bytes allowed",
+                    self.max_data_bytes
+                );
+            }
+            self.remaining -= written;
+            if let Some(mode) = file.unix_mode() {
+                std::fs::set_permissions(&out_path, std::fs::Permissions::from_mode(mode & 0o777))?;
+            }
+            files += 1;
+        }
+        Ok(files)
+    }
+}
+
+/// Merge imported settings into `existing`. Keys missing from `existing`
+/// are added; differing ones are replaced only with
+/// [`ConflictPolicy::Overwrite`].
+pub fn merge_settings(
+    existing: &mut Map<String, Value>,
+    incoming: &Map<String, Value>,
+    policy: ConflictPolicy,
+) -> SettingsOutcome {
+    let mut outcome = SettingsOutcome::default();
+    for (key, value) in incoming {
+        match existing.get(key) {
+            None => {
+                existing.insert(key.clone(), value.clone());
+                outcome.added.push(key.clone());
+            }
+            Some(current) if current != value && policy == ConflictPolicy::Overwrite => {
+                existing.insert(key.clone(), value.clone());
+                outcome.replaced.push(key.clone());
+            }
+            Some(_) => outcome.kept.push(key.clone()),
+        }
+    }
+    outcome
+}
+
+/// The first of `candidate(1)`, `candidate(2)`, ... not in `taken`.
+pub fn free_name(taken: &HashSet<String>, candidate: impl Fn(u32) -> String) -> String {
+    let mut n = 1;
+    loop {
+        let name = candidate(n);
+        if !taken.contains(&name) {
+            return name;
+        }
+        n += 1;
+    }
+}
+
+/// Active memories of a workspace's mmry store.
+pub fn read_memories(workspace: &Path) -> Result<Vec<MemoryRecord>> {
+    let memories = MemoryFile::open_workspace(&*workspace.to_string_lossy())
+        .active_memories()
+        .map_err(|e| anyhow!("reading workspace memories: {e:?}"))?;
+    Ok(memories
+        .into_iter()
+        .map(|entry| MemoryRecord {
+            id: entry.memory_id,
+            memory_type: format!("{:?}", entry.memory_type).to_lowercase(),
+            content: entry.content,
+            tags: entry.tags,
+            metadata: entry.metadata,
+            created_at: entry.created_at.to_rfc3339(),
+        })
+        .collect())
+}
+
+/// Add `records` to a workspace's mmry store, keeping their IDs. Memories
+/// the store already has are left alone. Returns how many were added.
+pub fn add_memories(workspace: &Path, records: &[MemoryRecord]) -> Result<usize> {
+    let memory_file = MemoryFile::open_workspace(&*workspace.to_string_lossy());
+    memory_file
+        .init(false)
+        .map_err(|e| anyhow!("initializing workspace memory file: {e:?}"))?;
+    let existing: HashSet<String> = memory_file
+        .active_memories()
+        .map_err(|e| anyhow!("reading workspace memories: {e:?}"))?
+        .into_iter()
+        .map(|entry| entry.memory_id)
+        .collect();
+
+    let mut added = 0;
+    for record in records.iter().filter(|r| !existing.contains(&r.id)) {
+        let memory_type = match record.memory_type.as_str() {
+            "episodic" => MemoryType::Episodic,
+            "procedural" => MemoryType::Procedural,
+            _ => MemoryType::Semantic,
+        };
+        let mut event = MemoryEvent::add(
+            record.content.clone(),
+            memory_type,
+            record.tags.clone(),
+            &AgentCtx::from_env(),
+        );
+        event.memory_id = record.id.clone();
+        if let Some(metadata) = record.metadata.as_object() {
+            for (key, value) in metadata {
+                event.metadata[key.as_str()] = value.clone();
+            }
+        }
+        memory_file
+            .append(&event)
+            .map_err(|e| anyhow!("appending memory event: {e:?}"))?;
+        added += 1;
+    }
+    Ok(added)
+}
+
+/// Where imported workspaces go.
+pub struct WorkspaceTarget {
+    pub root: PathBuf,
+    /// Linux user owning `root` in multi-user mode; workspaces are then
+    /// created through oqto-usermgr so the user owns the files.
+    pub linux_username: Option<String>,
+}
+
+/// Apply the archive's workspaces to `target`. Each workspace succeeds or
+/// fails on its own.
+pub fn import_workspaces(
+    archive: &mut EnvironmentArchive,
+    target: &WorkspaceTarget,
+    query: &ImportQuery,
+) -> Vec<ImportedItem> {
+    let mut taken: HashSet<String> = std::fs::read_dir(&target.root)
+        .map(|entries| {
+            entries
+                .flatten()
+                .filter_map(|entry| entry.file_name().to_str().map(str::to_string))
+                .collect()
+        })
+        .unwrap_or_default();
+
+    let workspaces = archive.manifest.workspaces.clone();
+    let mut items = Vec::with_capacity(workspaces.len());
+    for entry in &workspaces {
+        let exists = taken.contains(&entry.name);
+        let (dest_name, mut item) = match (exists, query.on_conflict) {
+            (false, _) => (
+                entry.name.clone(),
+                ImportedItem::new(&entry.name, ImportAction::Created),
+            ),
+            (true, ConflictPolicy::Skip) => {
+                items.push(ImportedItem::new(&entry.name, ImportAction::Skipped));
+                continue;
+            }
+            (true, ConflictPolicy::Overwrite) => (
+                entry.name.clone(),
+                ImportedItem::new(&entry.name, ImportAction::Replaced),
+            ),
+            (true, ConflictPolicy::Rename) => {
+                let name = free_name(&taken, |n| match n {
+                    1 => format!("{}-imported", entry.name),
+                    n => format!("{}-imported-{n}", entry.name),
+                });
+                let mut item = ImportedItem::new(&entry.name, ImportAction::Renamed);
+                item.imported_as = Some(name.clone());
+                (name, item)
+            }
+        };
+        taken.insert(dest_name.clone());
+
+        let with_data = entry.data && !query.skip_data;
+        if query.dry_run {
+            item.files = Some(if with_data { entry.files } else { 0 });
+            item.memories = Some(entry.memories.len());
+            items.push(item);
+            continue;
+        }
+        match import_workspace(
+            archive,
+            entry,
+            &target.root.join(&dest_name),
+            target,
+            with_data,
+        ) {
+            Ok((files, memories)) => {
+                item.files = Some(files);
+                item.memories = Some(memories);
+                items.push(item);
+            }
+            Err(e) => items.push(ImportedItem::failed(&entry.name, e)),
+        }
+    }
+    items
+}
+
+/// Create or update one workspace. Returns the files and memories written.
+fn import_workspace(
+    archive: &mut EnvironmentArchive,
+    entry: &WorkspaceEntry,
+    dest: &Path,
+    target: &WorkspaceTarget,
+    with_data: bool,
+) -> Result<(u64, usize)> {
+    let files = match &target.linux_username {
+        None => {
+            std::fs::create_dir_all(dest)
+                .with_context(|| format!("creating {}", dest.display()))?;
+            let files = if with_data {
+                archive.extract_workspace(&entry.name, dest)?
+            } else {
+                0
+            };
+            if let Some(meta) = &entry.meta {
+                write_workspace_meta(dest, meta)?;
+            }
+            files
+        }
+        Some(username) => {
+            // oqto-usermgr copies the staged files in and hands them to the
+            // user.
+            let staging = tempfile::tempdir().context("creating staging directory")?;
+            let files = if with_data {
+                archive.extract_workspace(&entry.name, staging.path())?
+            } else {
+                0
+            };
+            let mut overlay = Map::new();
+            if let Some(meta) = &entry.meta {
+                overlay.insert(
+                    ".oqto/workspace.toml".to_string(),
+                    Value::String(
+                        toml::to_string_pretty(meta).context("serializing workspace metadata")?,
+                    ),
+                );
+            }
+            crate::local::linux_users::usermgr_request(
+                "create-workspace",
+                serde_json::json!({
+                    "username": username,
+                    "path": dest.to_string_lossy(),
+                    "template_src": staging.path().to_string_lossy(),
+                    "files": overlay,
+                }),
+            )
+            .context("creating workspace via oqto-usermgr")?;
+            files
+        }
+    };
+    let memories = if entry.memories.is_empty() {
+        0
+    } else {
+        add_memories(dest, &entry.memories)?
+    };
+    Ok((files, memories))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn manifest(workspaces: Vec<WorkspaceEntry>) -> EnvironmentManifest {
+        EnvironmentManifest {
+            version: FORMAT_VERSION,
+            exported_at: chrono::Utc::now(),
+            source: EnvironmentSource {
+                username: "ada".to_string(),
+                display_name: "Ada".to_string(),
+                oqto_version: "0.0.0".to_string(),
+            },
+            settings: Map::new(),
+            macros: Vec::new(),
+            workspaces,
+        }
+    }
+
+    fn workspace(name: &str) -> WorkspaceEntry {
+        WorkspaceEntry {
+            name: name.to_string(),
+            meta: None,
+            memories: Vec::new(),
+            data: true,
+            files: 0,
+            bytes: 0,
+        }
+    }
+
+    #[test]
+    fn test_merge_settings_by_policy() {
+        let incoming = json!({"theme": "dark", "onboarding": {"completed": true}, "lang": "de"});
+        let incoming = incoming.as_object().unwrap();
+        let existing = json!({"theme": "light", "lang": "de"});
+
+        let mut merged = existing.as_object().unwrap().clone();
+        let outcome = merge_settings(&mut merged, incoming, ConflictPolicy::Skip);
+        assert_eq!(outcome.added, vec!["onboarding"]);
+        assert!(outcome.replaced.is_empty());
+        assert_eq!(merged["theme"], "light");
+
+        let mut merged = existing.as_object().unwrap().clone();
+        let outcome = merge_settings(&mut merged, incoming, ConflictPolicy::Overwrite);
+        assert_eq!(outcome.replaced, vec!["theme"]);
+        assert_eq!(outcome.kept, vec!["lang"]);
+        assert_eq!(merged["theme"], "dark");
+    }
+
+    #[test]
+    fn test_free_name_and_workspace_names() {
+        let taken: HashSet<String> = ["api".to_string(), "api-imported".to_string()].into();
+        let name = free_name(&taken, |n| match n {
+            1 => "api-imported".to_string(),
+            n => format!("api-imported-{n}"),
+        });
+        assert_eq!(name, "api-imported-2");
+
+        assert!(valid_workspace_name("my project"));
+        for name in ["", ".", "..", ".hidden", "a/b", "/abs"] {
+            assert!(!valid_workspace_name(name), "{name:?}");
+        }
+    }
+
+    #[test]
+    fn test_archive_round_trip_skips_excluded_files() {
+        let source = tempfile::tempdir().unwrap();
+        let project = source.path().join("api");
+        std::fs::create_dir_all(project.join("src")).unwrap();
+        std::fs::create_dir_all(project.join("node_modules/left-pad")).unwrap();
+        std::fs::write(project.join("src/main.rs"), "fn main() {}").unwrap();
+        std::fs::write(project.join("run.sh"), "#!/bin/sh\n").unwrap();
+        std::fs::set_permissions(
+            project.join("run.sh"),
+            std::fs::Permissions::from_mode(0o755),
+        )
+        .unwrap();
+        std::fs::write(project.join("node_modules/left-pad/index.js"), "x").unwrap();
+
+        let config = UserEnvConfig::default();
+        let (mut file, size) =
+            write_archive(&manifest(vec![workspace("api")]), source.path(), &config).unwrap();
+        let mut bytes = Vec::with_capacity(size as usize);
+        file.read_to_end(&mut bytes).unwrap();
+
+        let mut archive = EnvironmentArchive::open(Bytes::from(bytes.clone()), &config).unwrap();
+        assert_eq!(archive.manifest().source.username, "ada");
+        let dest = tempfile::tempdir().unwrap();
+        assert_eq!(archive.extract_workspace("api", dest.path()).unwrap(), 2);
+        assert_eq!(
+            std::fs::read_to_string(dest.path().join("src/main.rs")).unwrap(),
+            "fn main() {}"
+        );
+        let mode = std::fs::metadata(dest.path().join("run.sh"))
+            .unwrap()
+            .permissions()
+            .mode();
+        assert_eq!(mode & 0o777, 0o755);
+        assert!(!dest.path().join("node_modules").exists());
+
+        let tiny = UserEnvConfig {
+            max_data_bytes: 4,
+            ..UserEnvConfig::default()
+        };
+        assert!(EnvironmentArchive::open(Bytes::from(bytes), &tiny).is_err());
+    }
+}
@@ -0,0 +1,200 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::macros::SaveMacroRequest;
+use crate::workspace::meta::WorkspaceMeta;
+
+/// `[user_env]` configuration.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct UserEnvConfig {
+    /// Let users export and import their environment.
+    pub enabled: bool,
+    /// Most workspace file bytes (uncompressed) in one archive, on export
+    /// and import.
+    pub max_data_bytes: u64,
+    /// Globs left out of workspace data, on top of each workspace's
+    /// `.oqtoignore`.
+    pub data_exclude: Vec<String>,
+}
+
+impl Default for UserEnvConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            max_data_bytes: 2 * 1024 * 1024 * 1024,
+            data_exclude: vec![
+                "node_modules/".to_string(),
+                "target/".to_string(),
+                ".venv/".to_string(),
+                "__pycache__/".to_string(),
+            ],
+        }
+    }
+}
+
+/// Who and what an archive was exported from.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EnvironmentSource {
+    pub username: String,
+    pub display_name: String,
+    /// Server version that wrote the archive.
+    pub oqto_version: String,
+}
+
+/// `environment.json` at the root of an archive.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EnvironmentManifest {
+    /// Archive format version.
+    pub version: u32,
+    pub exported_at: DateTime<Utc>,
+    pub source: EnvironmentSource,
+    /// The user's settings object (onboarding state, language, unlocked
+    /// components, UI preferences).
+    #[serde(default)]
+    pub settings: serde_json::Map<String, serde_json::Value>,
+    /// Saved prompt macros.
+    #[serde(default)]
+    pub macros: Vec<SaveMacroRequest>,
+    #[serde(default)]
+    pub workspaces: Vec<WorkspaceEntry>,
+}
+
+/// One directory under the workspace root.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WorkspaceEntry {
+    /// Directory name.
+    pub name: String,
+    /// `.oqto/workspace.toml` (display name, language, pin).
+    #[serde(default)]
+    pub meta: Option<WorkspaceMeta>,
+    /// The workspace's mmry store.
+    #[serde(default)]
+    pub memories: Vec<MemoryRecord>,
+    /// Whether the archive carries the files, under `workspaces/<name>/`.
+    #[serde(default)]
+    pub data: bool,
+    /// Files and bytes carried.
+    #[serde(default)]
+    pub files: u64,
+    #[serde(default)]
+    pub bytes: u64,
+}
+
+/// An active memory of a workspace store.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MemoryRecord {
+    /// Kept on import, so importing twice does not duplicate memories.
+    pub id: String,
+    pub memory_type: String,
+    pub content: String,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    #[serde(default)]
+    pub metadata: serde_json::Value,
+    pub created_at: String,
+}
+
+/// Query of the export endpoints.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct ExportQuery {
+    /// Also carry the workspace files.
+    #[serde(default)]
+    pub include_data: bool,
+}
+
+/// What to do with an imported item that already exists.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ConflictPolicy {
+    /// Keep the existing item.
+    #[default]
+    Skip,
+    /// Replace it with the imported one. Workspace files are written over
+    /// the existing ones and memories are merged.
+    Overwrite,
+    /// Import under a new name. Settings keys cannot be renamed and are
+    /// kept as with `skip`.
+    Rename,
+}
+
+/// Query of the import endpoints.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct ImportQuery {
+    #[serde(default)]
+    pub on_conflict: ConflictPolicy,
+    /// Report what would happen without changing anything.
+    #[serde(default)]
+    pub dry_run: bool,
+    /// Leave workspace files in the archive out.
+    #[serde(default)]
+    pub skip_data: bool,
+}
+
+/// What an import did (or would do) with one item.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ImportAction {
+    Created,
+    Replaced,
+    Renamed,
+    Skipped,
+    Failed,
+}
+
+/// Settings keys by what the import did with them.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct SettingsOutcome {
+    pub added: Vec<String>,
+    pub replaced: Vec<String>,
+    pub kept: Vec<String>,
+}
+
+/// Outcome for a macro or workspace.
+#[derive(Debug, Clone, Serialize)]
+pub struct ImportedItem {
+    pub name: String,
+    pub action: ImportAction,
+    /// New name when renamed.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub imported_as: Option<String>,
+    /// Memories added (workspaces only).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub memories: Option<usize>,
+    /// Files written (workspaces only).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub files: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl ImportedItem {
+    pub fn new(name: &str, action: ImportAction) -> Self {
+        Self {
+            name: name.to_string(),
+            action,
+            imported_as: None,
+            memories: None,
+            files: None,
+            error: None,
+        }
+    }
+
+    pub fn failed(name: &str, error: impl std::fmt::Display) -> Self {
+        Self {
+            error: Some(format!("{error:#}")),
+            ..Self::new(name, ImportAction::Failed)
+        }
+    }
+}
+
+/// Response of an import.
+#[derive(Debug, Clone, Serialize)]
+pub struct ImportReport {
+    pub dry_run: bool,
+    pub source: EnvironmentSource,
+    pub exported_at: DateTime<Utc>,
+    pub settings: SettingsOutcome,
+    pub macros: Vec<ImportedItem>,
+    pub workspaces: Vec<ImportedItem>,
+}
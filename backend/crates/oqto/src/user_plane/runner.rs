@@ -241,6 +241,9 @@ impl UserPlane for RunnerUserPlane {
                 request.ttyd_port,
                 request.agent,
                 request.env,
+                request.fileserver_token_secret,
+                request.shell,
+                request.secrets,
             )
             .await
             .context("runner start_session")?;
@@ -625,6 +628,9 @@ mod tests {
                 ttyd_port: 4102,
                 agent: Some("pi".to_string()),
                 env: HashMap::new(),
+                fileserver_token_secret: None,
+                shell: None,
+                secrets: Default::default(),
             })
             .await
             .expect("start session");
@@ -1,5 +1,6 @@
 //! Types for user-plane operations.
 
+use oqto_runner::protocol::{SessionSecrets, ShellEnvironment};
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 use std::collections::HashMap;
@@ -94,6 +95,12 @@ pub struct StartSessionRequest {
     pub agent: Option<String>,
     /// Additional environment variables.
     pub env: HashMap<String, String>,
+    /// Secret the fileserver checks capability tokens against.
+    pub fileserver_token_secret: Option<String>,
+    /// Shell environment the workspace declares.
+    pub shell: Option<ShellEnvironment>,
+    /// The user's secrets for the session.
+    pub secrets: SessionSecrets,
 }
 
 /// Response from starting a session.
@@ -0,0 +1,261 @@
+//! Health of the external voice services (eaRS STT, kokorox TTS).
+//!
+//! The configured endpoints are probed every `voice.health_check_interval_secs`
+//! and the result is cached for `GET /api/meta/voice`. Voice is advertised in
+//! the capability registry as enabled while both endpoints answer, degraded
+//! once one of them failed `voice.health_failure_threshold` probes in a row,
+//! and disabled while voice mode is turned off. Each change of availability is
+//! broadcast as `voice.availability` so clients can grey out the mic button
+//! instead of failing when the proxy cannot reach the service.
+
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use tracing::info;
+
+use crate::api::VoiceState;
+use crate::capabilities::{CapabilityRegistry, Subsystem, probe};
+use crate::ws::{WsEvent, WsHub};
+
+/// Last probe result of one voice endpoint.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct EndpointHealth {
+    pub url: String,
+    /// Whether the endpoint counts as reachable. Stays true until
+    /// `consecutive_failures` reaches the failure threshold.
+    pub reachable: bool,
+    /// Error of the last probe, if it failed.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_error: Option<String>,
+    pub consecutive_failures: u32,
+    /// When the endpoint was last probed.
+    pub checked_at: DateTime<Utc>,
+}
+
+/// Cached voice service health.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct VoiceHealthStatus {
+    /// Whether voice mode is turned on.
+    pub enabled: bool,
+    /// Whether clients can use voice mode.
+    pub available: bool,
+    /// Why voice is unavailable.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stt: Option<EndpointHealth>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tts: Option<EndpointHealth>,
+}
+
+/// Polls the voice endpoints and publishes their availability.
+pub struct VoiceHealthMonitor {
+    voice: Arc<RwLock<VoiceState>>,
+    registry: Arc<CapabilityRegistry>,
+    hub: Arc<WsHub>,
+    failure_threshold: u32,
+    status: RwLock<VoiceHealthStatus>,
+}
+
+impl VoiceHealthMonitor {
+    pub fn new(
+        voice: Arc<RwLock<VoiceState>>,
+        registry: Arc<CapabilityRegistry>,
+        hub: Arc<WsHub>,
+        failure_threshold: u32,
+    ) -> Self {
+        let enabled = voice.read().map(|v| v.enabled).unwrap_or(false);
+        Self {
+            voice,
+            registry,
+            hub,
+            failure_threshold: failure_threshold.max(1),
+            status: RwLock::new(VoiceHealthStatus {
+                enabled,
+                available: enabled,
+                reason: (!enabled).then(|| "voice.enabled is false".to_string()),
+                stt: None,
+                tts: None,
+            }),
+        }
+    }
+
+    /// The cached health.
+    pub fn status(&self) -> VoiceHealthStatus {
+        self.status
+            .read()
+            .map(|status| status.clone())
+            .unwrap_or_else(|e| e.into_inner().clone())
+    }
+
+    /// Probe the endpoints, update the cache and the capability registry, and
+    /// broadcast `voice.availability` if availability changed.
+    pub async fn check(&self) -> VoiceHealthStatus {
+        let (enabled, stt_url, tts_url) = match self.voice.read() {
+            Ok(voice) => (voice.enabled, voice.stt_url.clone(), voice.tts_url.clone()),
+            Err(_) => return self.status(),
+        };
+        let previous = self.status();
+
+        let next = if enabled {
+            let stt = self.probe_endpoint(stt_url, previous.stt.as_ref()).await;
+            let tts = self.probe_endpoint(tts_url, previous.tts.as_ref()).await;
+            let failures: Vec<String> = [("STT server", &stt), ("TTS server", &tts)]
+                .into_iter()
+                .filter(|(_, endpoint)| !endpoint.reachable)
+                .map(|(label, endpoint)| {
+                    format!(
+                        "{label} unreachable at {}: {}",
+                        endpoint.url,
+                        endpoint.last_error.as_deref().unwrap_or("unknown error")
+                    )
+                })
+                .collect();
+            VoiceHealthStatus {
+                enabled,
+                available: failures.is_empty(),
+                reason: (!failures.is_empty()).then(|| failures.join("; ")),
+                stt: Some(stt),
+                tts: Some(tts),
+            }
+        } else {
+            VoiceHealthStatus {
+                enabled,
+                available: false,
+                reason: Some("voice.enabled is false".to_string()),
+                stt: None,
+                tts: None,
+            }
+        };
+
+        match (next.enabled, &next.reason) {
+            (false, _) => self
+                .registry
+                .disable(Subsystem::Voice, "voice.enabled is false"),
+            (true, None) => self.registry.enable(Subsystem::Voice),
+            (true, Some(reason)) => self.registry.degrade(Subsystem::Voice, reason.clone()),
+        }
+        if let Ok(mut status) = self.status.write() {
+            *status = next.clone();
+        }
+
+        if next.available != previous.available {
+            info!(
+                available = next.available,
+                reason = next.reason.as_deref().unwrap_or(""),
+                "Voice availability changed"
+            );
+            self.hub
+                .broadcast_to_all(WsEvent::VoiceAvailability {
+                    available: next.available,
+                    reason: next.reason.clone(),
+                })
+                .await;
+        }
+        next
+    }
+
+    async fn probe_endpoint(
+        &self,
+        url: String,
+        previous: Option<&EndpointHealth>,
+    ) -> EndpointHealth {
+        // Failures of an endpoint that changed URL start from scratch.
+        let previous = previous.filter(|p| p.url == url);
+        let result = probe(&url).await;
+        let consecutive_failures = match &result {
+            Ok(()) => 0,
+            Err(_) => previous.map_or(0, |p| p.consecutive_failures) + 1,
+        };
+        EndpointHealth {
+            reachable: consecutive_failures < self.failure_threshold,
+            last_error: result.err(),
+            consecutive_failures,
+            checked_at: Utc::now(),
+            url,
+        }
+    }
+
+    /// Check every `interval` until shutdown.
+    pub fn start(self: &Arc<Self>, interval: Duration) {
+        let monitor = Arc::clone(self);
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(interval);
+            interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+            loop {
+                interval.tick().await;
+                monitor.check().await;
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::capabilities::CapabilityState;
+
+    fn monitor(stt_url: String, tts_url: String, threshold: u32) -> VoiceHealthMonitor {
+        let voice = VoiceState {
+            enabled: true,
+            stt_url,
+            tts_url,
+            ..VoiceState::default()
+        };
+        VoiceHealthMonitor::new(
+            Arc::new(RwLock::new(voice)),
+            Arc::new(CapabilityRegistry::default()),
+            Arc::new(WsHub::new()),
+            threshold,
+        )
+    }
+
+    #[tokio::test]
+    async fn test_endpoint_goes_unavailable_after_threshold() {
+        let stt = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let tts = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let tts_port = tts.local_addr().unwrap().port();
+        let monitor = monitor(
+            format!("ws://{}", stt.local_addr().unwrap()),
+            format!("ws://127.0.0.1:{tts_port}"),
+            2,
+        );
+
+        let status = monitor.check().await;
+        assert!(status.available);
+        assert_eq!(
+            monitor.registry.get(Subsystem::Voice).state,
+            CapabilityState::Enabled
+        );
+
+        drop(tts);
+        let status = monitor.check().await;
+        assert!(status.available);
+        assert_eq!(status.tts.as_ref().unwrap().consecutive_failures, 1);
+
+        let status = monitor.check().await;
+        assert!(!status.available);
+        assert!(status.reason.unwrap().starts_with("TTS server unreachable"));
+        assert!(status.stt.unwrap().reachable);
+        let capability = monitor.registry.get(Subsystem::Voice);
+        assert_eq!(capability.state, CapabilityState::Degraded);
+        assert_eq!(monitor.status().tts.unwrap().consecutive_failures, 2);
+    }
+
+    #[tokio::test]
+    async fn test_disabled_voice_is_not_probed() {
+        let monitor = monitor("ws://127.0.0.1:1".into(), "ws://127.0.0.1:1".into(), 1);
+        monitor.voice.write().unwrap().enabled = false;
+
+        let status = monitor.check().await;
+        assert!(!status.enabled);
+        assert!(!status.available);
+        assert!(status.stt.is_none());
+        assert_eq!(
+            monitor.registry.get(Subsystem::Voice).state,
+            CapabilityState::Disabled
+        );
+    }
+}
@@ -0,0 +1,481 @@
+//! Dependency vulnerability scans of workspaces.
+//!
+//! When a watched workspace's manifests or lockfiles change, a scan is
+//! scheduled after a quiet period (`debounce_secs`, and no sooner than
+//! `min_interval_secs` after the previous scan). The workspace's runner
+//! invokes osv-scanner, `cargo audit` and/or `npm audit` as the workspace's
+//! user; the reports are parsed and merged here and stored per workspace.
+//! Vulnerabilities seen for the first time at or above
+//! `notify_min_severity` raise a notification for the user whose change
+//! triggered the scan. Scans can also be requested on demand.
+
+mod models;
+mod parse;
+mod repository;
+
+pub use models::{
+    ScanListQuery, ScanStatus, ScanTrigger, ScannerOutcome, ScannerStatus, Severity,
+    SeverityCounts, StartScanRequest, Vulnerability, VulnerabilityScanConfig, WorkspaceScan,
+    WorkspaceVulnerabilities,
+};
+pub use repository::VulnScanRepository;
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use anyhow::Result;
+use oqto_runner::client::RunnerClient;
+use oqto_runner::protocol::{DependencyScanner, DependencyScannerRun};
+use tokio::sync::Semaphore;
+use tokio::task::JoinHandle;
+use tracing::{debug, info, warn};
+
+use crate::ws::{WsEvent, WsHub};
+
+/// osv-scanner's exit code when it finds nothing to scan.
+const OSV_NO_PACKAGES_EXIT: i32 = 128;
+/// Most vulnerabilities listed in one notification.
+const MAX_NOTIFIED: usize = 5;
+
+/// File names whose changes can alter a workspace's dependencies.
+const DEPENDENCY_FILES: &[&str] = &[
+    "Cargo.toml",
+    "Cargo.lock",
+    "package.json",
+    "package-lock.json",
+    "npm-shrinkwrap.json",
+    "yarn.lock",
+    "pnpm-lock.yaml",
+    "bun.lock",
+    "requirements.txt",
+    "poetry.lock",
+    "Pipfile.lock",
+    "uv.lock",
+    "go.mod",
+    "go.sum",
+    "Gemfile.lock",
+    "composer.lock",
+];
+
+/// Whether a changed path (relative to the workspace) is a dependency
+/// manifest or lockfile. Files inside installed dependencies and build
+/// output do not count.
+pub fn is_dependency_file(rel_path: &str) -> bool {
+    let path = Path::new(rel_path);
+    let Some(name) = path.file_name().and_then(|n| n.to_str()) else {
+        return false;
+    };
+    DEPENDENCY_FILES.contains(&name)
+        && !path.components().any(|c| {
+            matches!(
+                c.as_os_str().to_str(),
+                Some("node_modules" | "target" | "vendor" | ".venv")
+            )
+        })
+}
+
+/// Vulnerability scan service: scheduling, running and notifying.
+pub struct VulnScanService {
+    repo: VulnScanRepository,
+    config: VulnerabilityScanConfig,
+    hub: Arc<WsHub>,
+    /// Debounced change-triggered scans waiting to start, by workspace.
+    pending: Mutex<HashMap<String, JoinHandle<()>>>,
+    permits: Semaphore,
+}
+
+impl VulnScanService {
+    pub fn new(repo: VulnScanRepository, config: VulnerabilityScanConfig, hub: Arc<WsHub>) -> Self {
+        let permits = Semaphore::new(config.max_concurrent_scans.max(1));
+        Self {
+            repo,
+            config,
+            hub,
+            pending: Mutex::new(HashMap::new()),
+            permits,
+        }
+    }
+
+    pub fn config(&self) -> &VulnerabilityScanConfig {
+        &self.config
+    }
+
+    pub fn repository(&self) -> &VulnScanRepository {
+        &self.repo
+    }
+
+    /// Schedule a scan after dependency files of `workspace_path` changed.
+    /// Further changes within the debounce window push the scan back.
+    pub fn schedule(
+        self: &Arc<Self>,
+        user_id: &str,
+        workspace_path: PathBuf,
+        runner: RunnerClient,
+    ) {
+        if !self.config.scan_on_change {
+            return;
+        }
+        let key = workspace_path.display().to_string();
+        let service = Arc::clone(self);
+        let user_id = user_id.to_string();
+        let task_key = key.clone();
+
+        let mut pending = self.pending.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(handle) = pending.remove(&key) {
+            handle.abort();
+        }
+        let handle = tokio::spawn(async move {
+            let mut delay = Duration::from_secs(service.config.debounce_secs);
+            match service.repo.seconds_since_last_scan(&task_key).await {
+                Ok(Some(elapsed)) => {
+                    let elapsed = Duration::from_secs(elapsed.max(0) as u64);
+                    let remaining = Duration::from_secs(service.config.min_interval_secs)
+                        .saturating_sub(elapsed);
+                    delay = delay.max(remaining);
+                }
+                Ok(None) => {}
+                Err(e) => warn!("Failed to read last vulnerability scan: {:#}", e),
+            }
+            tokio::time::sleep(delay).await;
+            // From here on the scan runs to completion; later changes
+            // schedule a new one.
+            service
+                .pending
+                .lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .remove(&task_key);
+            match service
+                .repo
+                .begin(&task_key, &user_id, ScanTrigger::FileChange)
+                .await
+            {
+                Ok(Some(scan)) => {
+                    service
+                        .execute(scan, &user_id, &workspace_path, &runner)
+                        .await
+                }
+                Ok(None) => {
+                    debug!(workspace_path = %task_key, "Vulnerability scan already running")
+                }
+                Err(e) => warn!("Failed to start vulnerability scan: {:#}", e),
+            }
+        });
+        debug!(workspace_path = %key, "Vulnerability scan scheduled");
+        pending.insert(key, handle);
+    }
+
+    /// Start a scan right away. Returns None if one is already running.
+    pub async fn scan_now(
+        self: &Arc<Self>,
+        user_id: &str,
+        workspace_path: PathBuf,
+        runner: RunnerClient,
+    ) -> Result<Option<WorkspaceScan>> {
+        let key = workspace_path.display().to_string();
+        let Some(scan) = self.repo.begin(&key, user_id, ScanTrigger::Manual).await? else {
+            return Ok(None);
+        };
+        if let Some(handle) = self
+            .pending
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .remove(&key)
+        {
+            handle.abort();
+        }
+        let service = Arc::clone(self);
+        let user_id = user_id.to_string();
+        let started = scan.clone();
+        tokio::spawn(async move {
+            service
+                .execute(started, &user_id, &workspace_path, &runner)
+                .await;
+        });
+        Ok(Some(scan))
+    }
+
+    /// Run the scanners of a begun scan and store the outcome.
+    async fn execute(
+        &self,
+        scan: WorkspaceScan,
+        user_id: &str,
+        workspace_path: &Path,
+        runner: &RunnerClient,
+    ) {
+        let _permit = match self.permits.acquire().await {
+            Ok(permit) => permit,
+            Err(_) => return,
+        };
+        info!(
+            scan_id = %scan.id,
+            workspace_path = %scan.workspace_path,
+            trigger = ?scan.triggered_by,
+            "Vulnerability scan started"
+        );
+        let result = runner
+            .scan_dependencies(
+                workspace_path,
+                self.config.scanners.clone(),
+                Some(self.config.timeout_secs),
+            )
+            .await;
+        let response = match result {
+            Ok(response) => response,
+            Err(e) => {
+                warn!(scan_id = %scan.id, "Vulnerability scan failed: {:#}", e);
+                if let Err(e) = self
+                    .repo
+                    .fail(&scan.id, &[], &format!("runner scan failed: {e:#}"))
+                    .await
+                {
+                    warn!("Failed to record vulnerability scan failure: {:#}", e);
+                }
+                return;
+            }
+        };
+
+        let (outcomes, findings) = collect_runs(&response.runs, workspace_path);
+        let reported = outcomes.iter().any(|o| o.status == ScannerStatus::Ok);
+        let all_skipped = outcomes.iter().all(|o| o.status == ScannerStatus::Skipped);
+        if !reported && !all_skipped {
+            warn!(scan_id = %scan.id, "No vulnerability scanner produced a report");
+            if let Err(e) = self
+                .repo
+                .fail(&scan.id, &outcomes, "no scanner produced a report")
+                .await
+            {
+                warn!("Failed to record vulnerability scan failure: {:#}", e);
+            }
+            return;
+        }
+
+        let new = match self.repo.complete(&scan.id, &outcomes, &findings).await {
+            Ok(new) => new,
+            Err(e) => {
+                warn!(scan_id = %scan.id, "Failed to store vulnerability findings: {:#}", e);
+                return;
+            }
+        };
+        info!(
+            scan_id = %scan.id,
+            workspace_path = %scan.workspace_path,
+            findings = findings.len(),
+            new = new.len(),
+            "Vulnerability scan completed"
+        );
+        self.notify(user_id, &scan, &new).await;
+    }
+
+    /// Tell the user about newly found vulnerabilities worth a notification.
+    async fn notify(&self, user_id: &str, scan: &WorkspaceScan, new: &[Vulnerability]) {
+        let notable: Vec<&Vulnerability> = new
+            .iter()
+            .filter(|v| v.severity >= self.config.notify_min_severity)
+            .collect();
+        let Some(worst) = notable.first() else {
+            return;
+        };
+        let workspace = Path::new(&scan.workspace_path)
+            .file_name()
+            .map(|n| n.to_string_lossy().into_owned())
+            .unwrap_or_else(|| scan.workspace_path.clone());
+        let (title, message) = match notable.as_slice() {
+            [only] => (
+                format!(
+                    "{} vulnerability in {}",
+                    capitalize(only.severity),
+                    only.package
+                ),
+                format!("{} in {}", describe(only), workspace),
+            ),
+            many => (
+                format!("{} new vulnerabilities in {}", many.len(), workspace),
+                many.iter()
+                    .take(MAX_NOTIFIED)
+                    .map(|v| describe(v))
+                    .collect::<Vec<_>>()
+                    .join("; "),
+            ),
+        };
+        let level = if worst.severity == Severity::Critical {
+            "error"
+        } else {
+            "warning"
+        };
+        self.hub
+            .send_to_user(
+                user_id,
+                WsEvent::Notification {
+                    level: level.to_string(),
+                    title,
+                    message,
+                    category: "security.vulnerability".to_string(),
+                    detail: Some(serde_json::json!({
+                        "scan_id": scan.id,
+                        "workspace_path": scan.workspace_path,
+                        "vulnerabilities": notable,
+                    })),
+                },
+            )
+            .await;
+    }
+}
+
+/// Scanner outcomes and merged findings of a runner response.
+fn collect_runs(
+    runs: &[DependencyScannerRun],
+    workspace_path: &Path,
+) -> (Vec<ScannerOutcome>, Vec<Vulnerability>) {
+    let mut outcomes = Vec::with_capacity(runs.len());
+    let mut findings = Vec::new();
+    for run in runs {
+        let mut outcome = ScannerOutcome {
+            scanner: run.scanner,
+            status: ScannerStatus::Ok,
+            detail: None,
+            findings: 0,
+            duration_ms: run.duration_ms,
+        };
+        if let Some(reason) = &run.skipped {
+            outcome.status = ScannerStatus::Skipped;
+            outcome.detail = Some(reason.clone());
+        } else if run.timed_out {
+            outcome.status = ScannerStatus::TimedOut;
+        } else if run.scanner == DependencyScanner::OsvScanner
+            && run.exit_code == Some(OSV_NO_PACKAGES_EXIT)
+            && run.stdout.trim().is_empty()
+        {
+            outcome.status = ScannerStatus::Skipped;
+            outcome.detail = Some("no package sources found".to_string());
+        } else {
+            match parse::parse_report(run.scanner, &run.stdout, workspace_path) {
+                Ok(found) => {
+                    outcome.findings = found.len();
+                    findings.extend(found);
+                }
+                Err(e) => {
+                    outcome.status = ScannerStatus::Failed;
+                    let stderr = run.stderr.trim();
+                    outcome.detail = Some(if stderr.is_empty() {
+                        format!("{e:#}")
+                    } else {
+                        stderr.to_string()
+                    });
+                }
+            }
+        }
+        outcomes.push(outcome);
+    }
+    (outcomes, parse::merge(findings))
+}
+
+/// One-line description of a finding for notifications.
+fn describe(v: &Vulnerability) -> String {
+    let mut text = format!("{} in {}", v.advisory_id, v.package);
+    if !v.version.is_empty() {
+        text.push_str(&format!(" {}", v.version));
+    }
+    if let Some(cve) = v.cve().filter(|cve| *cve != v.advisory_id) {
+        text.push_str(&format!(" ({cve})"));
+    }
+    if let Some(fixed) = &v.fixed_version {
+        text.push_str(&format!(", fixed in {fixed}"));
+    }
+    text
+}
+
+fn capitalize(severity: Severity) -> String {
+    let label = severity.to_string();
+    let mut chars = label.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => label,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_dependency_file() {
+        assert!(is_dependency_file("Cargo.lock"));
+        assert!(is_dependency_file("web/package-lock.json"));
+        assert!(!is_dependency_file("node_modules/left-pad/package.json"));
+        assert!(!is_dependency_file("target/package/x/Cargo.toml"));
+        assert!(!is_dependency_file("src/main.rs"));
+    }
+
+    #[test]
+    fn test_collect_runs_statuses() {
+        let run = |scanner, skipped: Option<&str>, exit_code, stdout: &str| DependencyScannerRun {
+            scanner,
+            skipped: skipped.map(ToString::to_string),
+            exit_code,
+            stdout: stdout.to_string(),
+            stderr: "error: boom".to_string(),
+            timed_out: false,
+            truncated: false,
+            duration_ms: 5,
+        };
+        let runs = [
+            run(DependencyScanner::OsvScanner, None, Some(128), ""),
+            run(
+                DependencyScanner::CargoAudit,
+                Some("no Cargo.lock"),
+                None,
+                "",
+            ),
+            run(
+                DependencyScanner::NpmAudit,
+                None,
+                Some(1),
+                r#"{"vulnerabilities":{"a":{"via":[{"name":"a","url":"https://github.com/advisories/GHSA-1","severity":"high"}]}}}"#,
+            ),
+        ];
+        let (outcomes, findings) = collect_runs(&runs, Path::new("/ws"));
+        let statuses: Vec<_> = outcomes.iter().map(|o| o.status).collect();
+        assert_eq!(
+            statuses,
+            [
+                ScannerStatus::Skipped,
+                ScannerStatus::Skipped,
+                ScannerStatus::Ok
+            ]
+        );
+        assert_eq!(findings.len(), 1);
+        assert_eq!(findings[0].severity, Severity::High);
+
+        let (outcomes, _) = collect_runs(
+            &[run(DependencyScanner::CargoAudit, None, Some(2), "")],
+            Path::new("/ws"),
+        );
+        assert_eq!(outcomes[0].status, ScannerStatus::Failed);
+        assert_eq!(outcomes[0].detail.as_deref(), Some("error: boom"));
+    }
+
+    #[test]
+    fn test_describe() {
+        let v = Vulnerability {
+            ecosystem: "crates.io".to_string(),
+            package: "time".to_string(),
+            version: "0.1.43".to_string(),
+            advisory_id: "RUSTSEC-2020-0071".to_string(),
+            aliases: vec!["CVE-2020-26235".to_string()],
+            scanners: vec![DependencyScanner::CargoAudit],
+            severity: Severity::Critical,
+            cvss_score: None,
+            summary: None,
+            fixed_version: Some(">=0.2.23".to_string()),
+            source_path: None,
+            first_seen_at: String::new(),
+        };
+        assert_eq!(
+            describe(&v),
+            "RUSTSEC-2020-0071 in time 0.1.43 (CVE-2020-26235), fixed in >=0.2.23"
+        );
+        assert_eq!(capitalize(Severity::Critical), "Critical");
+    }
+}
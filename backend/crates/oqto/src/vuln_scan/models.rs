@@ -0,0 +1,245 @@
+use oqto_runner::protocol::DependencyScanner;
+use serde::{Deserialize, Serialize};
+
+/// Dependency vulnerability scan configuration.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct VulnerabilityScanConfig {
+    /// Scan workspace dependencies for known vulnerabilities.
+    pub enabled: bool,
+    /// Scanners the runner tries, in order. Missing ones are skipped.
+    pub scanners: Vec<DependencyScanner>,
+    /// Scan when a watched workspace's manifests or lockfiles change.
+    pub scan_on_change: bool,
+    /// Quiet period after the last dependency file change before scanning.
+    pub debounce_secs: u64,
+    /// Least time between two change-triggered scans of one workspace.
+    pub min_interval_secs: u64,
+    /// Time limit per scanner.
+    pub timeout_secs: u64,
+    /// Newly found vulnerabilities at or above this severity raise a
+    /// notification.
+    pub notify_min_severity: Severity,
+    /// Most scans running at once across all workspaces.
+    pub max_concurrent_scans: usize,
+}
+
+impl Default for VulnerabilityScanConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            scanners: vec![
+                DependencyScanner::OsvScanner,
+                DependencyScanner::CargoAudit,
+                DependencyScanner::NpmAudit,
+            ],
+            scan_on_change: true,
+            debounce_secs: 60,
+            min_interval_secs: 900,
+            timeout_secs: 300,
+            notify_min_severity: Severity::Critical,
+            max_concurrent_scans: 2,
+        }
+    }
+}
+
+/// Severity of a vulnerability, ordered from least to most severe.
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    Default,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    Hash,
+    Serialize,
+    Deserialize,
+    sqlx::Type,
+)]
+#[serde(rename_all = "snake_case")]
+#[sqlx(rename_all = "snake_case")]
+pub enum Severity {
+    /// The scanner reported no usable rating.
+    #[default]
+    Unknown,
+    Low,
+    Medium,
+    High,
+    Critical,
+}
+
+impl Severity {
+    /// Rating of a CVSS base score.
+    pub fn from_cvss(score: f64) -> Self {
+        match score {
+            s if s >= 9.0 => Self::Critical,
+            s if s >= 7.0 => Self::High,
+            s if s >= 4.0 => Self::Medium,
+            s if s > 0.0 => Self::Low,
+            _ => Self::Unknown,
+        }
+    }
+
+    /// Parse a textual rating as used by GitHub advisories and npm.
+    pub fn from_label(label: &str) -> Self {
+        match label.trim().to_ascii_lowercase().as_str() {
+            "critical" => Self::Critical,
+            "high" => Self::High,
+            "moderate" | "medium" => Self::Medium,
+            "low" => Self::Low,
+            _ => Self::Unknown,
+        }
+    }
+}
+
+impl std::fmt::Display for Severity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::Unknown => "unknown",
+            Self::Low => "low",
+            Self::Medium => "medium",
+            Self::High => "high",
+            Self::Critical => "critical",
+        })
+    }
+}
+
+/// State of a workspace's latest scan.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, sqlx::Type)]
+#[serde(rename_all = "snake_case")]
+#[sqlx(rename_all = "snake_case")]
+pub enum ScanStatus {
+    Running,
+    /// Every scanner ran or was skipped; findings are current.
+    Completed,
+    /// The runner could not be reached or no scanner produced a report.
+    /// Findings of the previous scan are kept.
+    Failed,
+}
+
+/// What started a scan.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, sqlx::Type)]
+#[serde(rename_all = "snake_case")]
+#[sqlx(rename_all = "snake_case")]
+pub enum ScanTrigger {
+    /// A dependency manifest or lockfile changed.
+    FileChange,
+    /// `POST /api/workspaces/vulnerabilities/scan`.
+    Manual,
+}
+
+/// How one scanner fared.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ScannerStatus {
+    Ok,
+    /// Not installed or not applicable (no lockfile).
+    Skipped,
+    /// Ran but produced no readable report.
+    Failed,
+    TimedOut,
+}
+
+/// Outcome of one scanner in a scan.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ScannerOutcome {
+    pub scanner: DependencyScanner,
+    pub status: ScannerStatus,
+    /// Skip reason or error output.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub detail: Option<String>,
+    /// Vulnerabilities the scanner reported, before deduplication.
+    #[serde(default)]
+    pub findings: usize,
+    #[serde(default)]
+    pub duration_ms: u64,
+}
+
+/// Number of current findings per severity.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct SeverityCounts {
+    pub critical: i64,
+    pub high: i64,
+    pub medium: i64,
+    pub low: i64,
+    pub unknown: i64,
+}
+
+/// The latest scan of a workspace.
+#[derive(Debug, Clone, Serialize)]
+pub struct WorkspaceScan {
+    /// Stable per workspace; used as `{id}` in the API.
+    pub id: String,
+    pub workspace_path: String,
+    pub user_id: Option<String>,
+    pub status: ScanStatus,
+    pub triggered_by: ScanTrigger,
+    pub scanners: Vec<ScannerOutcome>,
+    pub error: Option<String>,
+    pub started_at: String,
+    pub finished_at: Option<String>,
+    pub counts: SeverityCounts,
+}
+
+/// A known vulnerability in one dependency.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Vulnerability {
+    /// Package ecosystem as named by OSV (`crates.io`, `npm`, `PyPI`, ...).
+    pub ecosystem: String,
+    pub package: String,
+    /// Installed version; empty when the scanner only reports a range.
+    pub version: String,
+    /// Primary advisory id (RUSTSEC-, GHSA-, PYSEC-, ...).
+    pub advisory_id: String,
+    /// Other ids of the same advisory, usually including the CVE.
+    pub aliases: Vec<String>,
+    /// Scanners that reported it.
+    pub scanners: Vec<DependencyScanner>,
+    pub severity: Severity,
+    pub cvss_score: Option<f64>,
+    pub summary: Option<String>,
+    /// Earliest version that fixes it, if known.
+    pub fixed_version: Option<String>,
+    /// Lockfile the finding came from, relative to the workspace.
+    pub source_path: Option<String>,
+    /// When a scan of this workspace first reported it. Empty until stored.
+    pub first_seen_at: String,
+}
+
+impl Vulnerability {
+    /// Advisory id and aliases.
+    pub fn ids(&self) -> impl Iterator<Item = &str> {
+        std::iter::once(self.advisory_id.as_str()).chain(self.aliases.iter().map(String::as_str))
+    }
+
+    /// CVE id among the advisory's ids, if any.
+    pub fn cve(&self) -> Option<&str> {
+        self.ids().find(|id| id.starts_with("CVE-"))
+    }
+}
+
+/// Response of `GET /api/workspaces/{id}/vulnerabilities`.
+#[derive(Debug, Clone, Serialize)]
+pub struct WorkspaceVulnerabilities {
+    #[serde(flatten)]
+    pub scan: WorkspaceScan,
+    /// Most severe first.
+    pub vulnerabilities: Vec<Vulnerability>,
+}
+
+/// Request body for a manual scan.
+#[derive(Debug, Clone, Deserialize)]
+pub struct StartScanRequest {
+    pub workspace_path: String,
+}
+
+/// Filters for listing scans.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct ScanListQuery {
+    /// Only this workspace (any scan the caller can access). Without it,
+    /// scans the caller triggered are listed.
+    #[serde(default)]
+    pub workspace_path: Option<String>,
+}
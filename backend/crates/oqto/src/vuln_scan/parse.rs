@@ -0,0 +1,481 @@
+//! Reading scanner reports.
+//!
+//! The runner hands back each scanner's JSON report unparsed; the formats
+//! are read leniently (missing fields become empty values) so minor version
+//! differences between scanner releases do not fail a scan.
+
+use std::path::Path;
+
+use anyhow::{Context, Result};
+use oqto_runner::protocol::DependencyScanner;
+use serde_json::Value;
+
+use super::{Severity, Vulnerability};
+
+/// Vulnerabilities in one scanner's report.
+pub fn parse_report(
+    scanner: DependencyScanner,
+    stdout: &str,
+    workspace: &Path,
+) -> Result<Vec<Vulnerability>> {
+    let report: Value = serde_json::from_str(stdout.trim())
+        .with_context(|| format!("{scanner:?} did not produce a JSON report"))?;
+    Ok(match scanner {
+        DependencyScanner::OsvScanner => parse_osv(&report, workspace),
+        DependencyScanner::CargoAudit => parse_cargo_audit(&report),
+        DependencyScanner::NpmAudit => parse_npm_audit(&report),
+    })
+}
+
+/// Merge reports of the same advisory for the same package (by id or
+/// alias), keeping the highest rating. Sorted most severe first.
+pub fn merge(findings: Vec<Vulnerability>) -> Vec<Vulnerability> {
+    let mut merged: Vec<Vulnerability> = Vec::with_capacity(findings.len());
+    for finding in findings {
+        let existing = merged.iter_mut().find(|m| {
+            m.package == finding.package
+                && m.ecosystem.eq_ignore_ascii_case(&finding.ecosystem)
+                && (m.version == finding.version
+                    || m.version.is_empty()
+                    || finding.version.is_empty())
+                && m.ids().any(|id| finding.ids().any(|other| other == id))
+        });
+        let Some(existing) = existing else {
+            merged.push(finding);
+            continue;
+        };
+        for scanner in finding.scanners {
+            if !existing.scanners.contains(&scanner) {
+                existing.scanners.push(scanner);
+            }
+        }
+        for id in std::iter::once(finding.advisory_id).chain(finding.aliases) {
+            if id != existing.advisory_id && !existing.aliases.contains(&id) {
+                existing.aliases.push(id);
+            }
+        }
+        existing.severity = existing.severity.max(finding.severity);
+        existing.cvss_score = match (existing.cvss_score, finding.cvss_score) {
+            (Some(a), Some(b)) => Some(a.max(b)),
+            (a, b) => a.or(b),
+        };
+        if existing.version.is_empty() {
+            existing.version = finding.version;
+        }
+        existing.summary = existing.summary.take().or(finding.summary);
+        existing.fixed_version = existing.fixed_version.take().or(finding.fixed_version);
+        existing.source_path = existing.source_path.take().or(finding.source_path);
+    }
+    merged.sort_by(|a, b| {
+        b.severity
+            .cmp(&a.severity)
+            .then_with(|| a.package.cmp(&b.package))
+            .then_with(|| a.advisory_id.cmp(&b.advisory_id))
+    });
+    merged
+}
+
+fn str_field(value: &Value, key: &str) -> Option<String> {
+    value
+        .get(key)
+        .and_then(Value::as_str)
+        .map(str::trim)
+        .filter(|s| !s.is_empty())
+        .map(ToString::to_string)
+}
+
+fn string_list(value: Option<&Value>) -> Vec<String> {
+    value
+        .and_then(Value::as_array)
+        .map(|items| {
+            items
+                .iter()
+                .filter_map(Value::as_str)
+                .map(ToString::to_string)
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+fn empty_finding(scanner: DependencyScanner) -> Vulnerability {
+    Vulnerability {
+        ecosystem: String::new(),
+        package: String::new(),
+        version: String::new(),
+        advisory_id: String::new(),
+        aliases: Vec::new(),
+        scanners: vec![scanner],
+        severity: Severity::Unknown,
+        cvss_score: None,
+        summary: None,
+        fixed_version: None,
+        source_path: None,
+        first_seen_at: String::new(),
+    }
+}
+
+/// `osv-scanner --format json`: `results[].packages[].vulnerabilities[]`.
+fn parse_osv(report: &Value, workspace: &Path) -> Vec<Vulnerability> {
+    let mut findings = Vec::new();
+    let results = report.get("results").and_then(Value::as_array);
+    for result in results.into_iter().flatten() {
+        let source_path = result
+            .pointer("/source/path")
+            .and_then(Value::as_str)
+            .map(|p| {
+                Path::new(p)
+                    .strip_prefix(workspace)
+                    .unwrap_or(Path::new(p))
+                    .display()
+                    .to_string()
+            });
+        let packages = result.get("packages").and_then(Value::as_array);
+        for package in packages.into_iter().flatten() {
+            let info = package.get("package").unwrap_or(&Value::Null);
+            let Some(name) = str_field(info, "name") else {
+                continue;
+            };
+            let groups = package.get("groups").and_then(Value::as_array);
+            let vulns = package.get("vulnerabilities").and_then(Value::as_array);
+            for vuln in vulns.into_iter().flatten() {
+                let Some(id) = str_field(vuln, "id") else {
+                    continue;
+                };
+                // The group score covers all aliases of an advisory.
+                let group_score = groups
+                    .into_iter()
+                    .flatten()
+                    .find(|g| string_list(g.get("ids")).contains(&id))
+                    .and_then(|g| g.get("max_severity"))
+                    .and_then(|s| match s {
+                        Value::String(s) => s.parse::<f64>().ok(),
+                        other => other.as_f64(),
+                    });
+                let vector_score = vuln
+                    .get("severity")
+                    .and_then(Value::as_array)
+                    .into_iter()
+                    .flatten()
+                    .filter_map(|s| s.get("score").and_then(Value::as_str))
+                    .filter_map(cvss3_base_score)
+                    .reduce(f64::max);
+                let cvss_score = group_score.or(vector_score).filter(|s| *s > 0.0);
+                let severity = match cvss_score {
+                    Some(score) => Severity::from_cvss(score),
+                    None => vuln
+                        .pointer("/database_specific/severity")
+                        .and_then(Value::as_str)
+                        .map(Severity::from_label)
+                        .unwrap_or_default(),
+                };
+                let fixed_version = vuln
+                    .get("affected")
+                    .and_then(Value::as_array)
+                    .into_iter()
+                    .flatten()
+                    .filter(|a| {
+                        a.pointer("/package/name").and_then(Value::as_str) == Some(name.as_str())
+                    })
+                    .flat_map(|a| {
+                        a.get("ranges")
+                            .and_then(Value::as_array)
+                            .into_iter()
+                            .flatten()
+                    })
+                    .flat_map(|r| {
+                        r.get("events")
+                            .and_then(Value::as_array)
+                            .into_iter()
+                            .flatten()
+                    })
+                    .find_map(|e| str_field(e, "fixed"));
+                findings.push(Vulnerability {
+                    ecosystem: str_field(info, "ecosystem").unwrap_or_default(),
+                    package: name.clone(),
+                    version: str_field(info, "version").unwrap_or_default(),
+                    advisory_id: id,
+                    aliases: string_list(vuln.get("aliases")),
+                    severity,
+                    cvss_score,
+                    summary: str_field(vuln, "summary"),
+                    fixed_version,
+                    source_path: source_path.clone(),
+                    ..empty_finding(DependencyScanner::OsvScanner)
+                });
+            }
+        }
+    }
+    findings
+}
+
+/// `cargo audit --json`: `vulnerabilities.list[]`.
+fn parse_cargo_audit(report: &Value) -> Vec<Vulnerability> {
+    let list = report
+        .pointer("/vulnerabilities/list")
+        .and_then(Value::as_array);
+    list.into_iter()
+        .flatten()
+        .filter_map(|entry| {
+            let advisory = entry.get("advisory")?;
+            let package = entry.get("package").unwrap_or(&Value::Null);
+            let cvss_score = advisory
+                .get("cvss")
+                .and_then(Value::as_str)
+                .and_then(cvss3_base_score)
+                .filter(|s| *s > 0.0);
+            Some(Vulnerability {
+                ecosystem: "crates.io".to_string(),
+                package: str_field(package, "name").or_else(|| str_field(advisory, "package"))?,
+                version: str_field(package, "version").unwrap_or_default(),
+                advisory_id: str_field(advisory, "id")?,
+                aliases: string_list(advisory.get("aliases")),
+                severity: cvss_score.map(Severity::from_cvss).unwrap_or_default(),
+                cvss_score,
+                summary: str_field(advisory, "title"),
+                fixed_version: string_list(entry.pointer("/versions/patched"))
+                    .into_iter()
+                    .next(),
+                source_path: Some("Cargo.lock".to_string()),
+                ..empty_finding(DependencyScanner::CargoAudit)
+            })
+        })
+        .collect()
+}
+
+/// `npm audit --json` (report version 2): advisories are the object entries
+/// of `vulnerabilities.<name>.via`; string entries only point at the
+/// vulnerable dependency and are reported under that dependency's name.
+fn parse_npm_audit(report: &Value) -> Vec<Vulnerability> {
+    let Some(packages) = report.get("vulnerabilities").and_then(Value::as_object) else {
+        return Vec::new();
+    };
+    let mut findings = Vec::new();
+    for (name, entry) in packages {
+        let fixed_version = entry
+            .get("fixAvailable")
+            .filter(|fix| fix.get("name").and_then(Value::as_str) == Some(name.as_str()))
+            .and_then(|fix| str_field(fix, "version"));
+        let via = entry.get("via").and_then(Value::as_array);
+        for advisory in via.into_iter().flatten().filter(|v| v.is_object()) {
+            if str_field(advisory, "name").is_some_and(|n| &n != name) {
+                continue;
+            }
+            let url = str_field(advisory, "url").unwrap_or_default();
+            let ghsa = url
+                .rsplit('/')
+                .next()
+                .filter(|id| id.starts_with("GHSA-"))
+                .map(ToString::to_string);
+            let Some(advisory_id) = ghsa.or_else(|| {
+                advisory
+                    .get("source")
+                    .filter(|s| !s.is_null())
+                    .map(|s| match s {
+                        Value::String(s) => format!("npm-{s}"),
+                        other => format!("npm-{other}"),
+                    })
+            }) else {
+                continue;
+            };
+            let cvss_score = advisory
+                .pointer("/cvss/score")
+                .and_then(Value::as_f64)
+                .filter(|s| *s > 0.0);
+            let severity = match cvss_score {
+                Some(score) => Severity::from_cvss(score),
+                None => advisory
+                    .get("severity")
+                    .and_then(Value::as_str)
+                    .map(Severity::from_label)
+                    .unwrap_or_default(),
+            };
+            findings.push(Vulnerability {
+                ecosystem: "npm".to_string(),
+                package: name.clone(),
+                advisory_id,
+                severity,
+                cvss_score,
+                summary: str_field(advisory, "title"),
+                fixed_version: fixed_version.clone(),
+                source_path: Some("package-lock.json".to_string()),
+                ..empty_finding(DependencyScanner::NpmAudit)
+            });
+        }
+    }
+    findings
+}
+
+/// CVSS v3.x base score of a vector string; None for other versions or
+/// malformed vectors.
+pub fn cvss3_base_score(vector: &str) -> Option<f64> {
+    let mut parts = vector.trim().split('/');
+    if !parts.next()?.starts_with("CVSS:3") {
+        return None;
+    }
+    let metric = |name: &str| {
+        vector
+            .split('/')
+            .filter_map(|p| p.split_once(':'))
+            .find(|(k, _)| *k == name)
+            .map(|(_, v)| v)
+    };
+    let scope_changed = match metric("S")? {
+        "U" => false,
+        "C" => true,
+        _ => return None,
+    };
+    let av = match metric("AV")? {
+        "N" => 0.85,
+        "A" => 0.62,
+        "L" => 0.55,
+        "P" => 0.2,
+        _ => return None,
+    };
+    let ac = match metric("AC")? {
+        "L" => 0.77,
+        "H" => 0.44,
+        _ => return None,
+    };
+    let pr = match (metric("PR")?, scope_changed) {
+        ("N", _) => 0.85,
+        ("L", false) => 0.62,
+        ("L", true) => 0.68,
+        ("H", false) => 0.27,
+        ("H", true) => 0.5,
+        _ => return None,
+    };
+    let ui = match metric("UI")? {
+        "N" => 0.85,
+        "R" => 0.62,
+        _ => return None,
+    };
+    let cia = |name: &str| match metric(name)? {
+        "H" => Some(0.56),
+        "L" => Some(0.22),
+        "N" => Some(0.0),
+        _ => None,
+    };
+    let (c, i, a) = (cia("C")?, cia("I")?, cia("A")?);
+
+    let iss = 1.0 - (1.0 - c) * (1.0 - i) * (1.0 - a);
+    let impact = if scope_changed {
+        7.52 * (iss - 0.029) - 3.25 * f64::powi(iss - 0.02, 15)
+    } else {
+        6.42 * iss
+    };
+    if impact <= 0.0 {
+        return Some(0.0);
+    }
+    let exploitability = 8.22 * av * ac * pr * ui;
+    let base = if scope_changed {
+        1.08 * (impact + exploitability)
+    } else {
+        impact + exploitability
+    };
+    Some(round_up(base.min(10.0)))
+}
+
+/// CVSS 3.1 "Roundup": smallest one-decimal number not below `value`.
+fn round_up(value: f64) -> f64 {
+    let int = (value * 100_000.0).round() as i64;
+    if int % 10_000 == 0 {
+        int as f64 / 100_000.0
+    } else {
+        ((int / 10_000) + 1) as f64 / 10.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cvss3_base_score() {
+        assert_eq!(
+            cvss3_base_score("CVSS:3.1/AV:N/AC:L/PR:N/UI:N/S:U/C:H/I:H/A:H"),
+            Some(9.8)
+        );
+        assert_eq!(
+            cvss3_base_score("CVSS:3.1/AV:N/AC:L/PR:N/UI:R/S:C/C:L/I:L/A:N"),
+            Some(6.1)
+        );
+        assert_eq!(
+            cvss3_base_score("CVSS:3.0/AV:N/AC:H/PR:N/UI:N/S:U/C:N/I:N/A:H"),
+            Some(5.9)
+        );
+        assert_eq!(
+            cvss3_base_score("CVSS:3.1/AV:L/AC:L/PR:L/UI:N/S:U/C:N/I:N/A:N"),
+            Some(0.0)
+        );
+        assert_eq!(cvss3_base_score("AV:N/AC:L/Au:N/C:P/I:P/A:P"), None);
+        assert_eq!(cvss3_base_score("CVSS:3.1/AV:X"), None);
+    }
+
+    #[test]
+    fn test_parse_osv_report() {
+        let report = r#"{"results":[{"source":{"path":"/ws/app/Cargo.lock","type":"lockfile"},
+            "packages":[{"package":{"name":"time","version":"0.1.43","ecosystem":"crates.io"},
+              "groups":[{"ids":["GHSA-wcg3-cvx6-7396","RUSTSEC-2020-0071"],"max_severity":"6.2"}],
+              "vulnerabilities":[{"id":"RUSTSEC-2020-0071","aliases":["CVE-2020-26235","GHSA-wcg3-cvx6-7396"],
+                "summary":"Potential segfault in the time crate",
+                "affected":[{"package":{"name":"time","ecosystem":"crates.io"},
+                  "ranges":[{"type":"SEMVER","events":[{"introduced":"0.0.0-0"},{"fixed":"0.2.23"}]}]}]}]}]}]}"#;
+        let findings =
+            parse_report(DependencyScanner::OsvScanner, report, Path::new("/ws")).unwrap();
+        assert_eq!(findings.len(), 1);
+        let f = &findings[0];
+        assert_eq!(f.package, "time");
+        assert_eq!(f.version, "0.1.43");
+        assert_eq!(f.advisory_id, "RUSTSEC-2020-0071");
+        assert_eq!(f.cve(), Some("CVE-2020-26235"));
+        assert_eq!(f.severity, Severity::Medium);
+        assert_eq!(f.fixed_version.as_deref(), Some("0.2.23"));
+        assert_eq!(f.source_path.as_deref(), Some("app/Cargo.lock"));
+
+        assert!(parse_report(DependencyScanner::OsvScanner, "oops", Path::new("/ws")).is_err());
+    }
+
+    #[test]
+    fn test_parse_npm_and_cargo_and_merge() {
+        let npm = r#"{"auditReportVersion":2,"vulnerabilities":{
+            "minimist":{"name":"minimist","severity":"critical","via":[{"source":1096,"name":"minimist",
+              "title":"Prototype Pollution in minimist","url":"https://github.com/advisories/GHSA-xvch-5gv4-984h",
+              "severity":"critical","cvss":{"score":9.8},"range":"<0.2.4"}],
+              "fixAvailable":{"name":"minimist","version":"1.2.8","isSemVerMajor":true}},
+            "mkdirp":{"name":"mkdirp","severity":"critical","via":["minimist"],"fixAvailable":true}}}"#;
+        let npm = parse_report(DependencyScanner::NpmAudit, npm, Path::new("/ws")).unwrap();
+        assert_eq!(npm.len(), 1);
+        assert_eq!(npm[0].advisory_id, "GHSA-xvch-5gv4-984h");
+        assert_eq!(npm[0].severity, Severity::Critical);
+        assert_eq!(npm[0].fixed_version.as_deref(), Some("1.2.8"));
+
+        let cargo = r#"{"vulnerabilities":{"found":true,"count":1,"list":[{
+            "advisory":{"id":"RUSTSEC-2020-0071","package":"time","title":"Potential segfault",
+              "aliases":["CVE-2020-26235"],"cvss":"CVSS:3.1/AV:N/AC:H/PR:N/UI:N/S:U/C:N/I:N/A:H"},
+            "versions":{"patched":[">=0.2.23"]},"package":{"name":"time","version":"0.1.43"}}]}}"#;
+        let cargo = parse_report(DependencyScanner::CargoAudit, cargo, Path::new("/ws")).unwrap();
+        assert_eq!(cargo[0].severity, Severity::Medium);
+        assert_eq!(cargo[0].cvss_score, Some(5.9));
+
+        let osv = Vulnerability {
+            ecosystem: "crates.io".to_string(),
+            package: "time".to_string(),
+            version: "0.1.43".to_string(),
+            advisory_id: "GHSA-wcg3-cvx6-7396".to_string(),
+            aliases: vec!["CVE-2020-26235".to_string()],
+            severity: Severity::High,
+            ..empty_finding(DependencyScanner::OsvScanner)
+        };
+        let merged = merge(npm.into_iter().chain(cargo).chain([osv]).collect());
+        assert_eq!(merged.len(), 2);
+        assert_eq!(merged[0].package, "minimist");
+        let time = &merged[1];
+        assert_eq!(time.advisory_id, "RUSTSEC-2020-0071");
+        assert_eq!(time.severity, Severity::High);
+        assert_eq!(
+            time.scanners,
+            [DependencyScanner::CargoAudit, DependencyScanner::OsvScanner]
+        );
+        assert!(time.aliases.contains(&"GHSA-wcg3-cvx6-7396".to_string()));
+    }
+}
@@ -0,0 +1,426 @@
+use std::collections::HashMap;
+
+use anyhow::{Context, Result};
+use sqlx::{FromRow, SqlitePool};
+
+use super::{
+    ScanStatus, ScanTrigger, ScannerOutcome, Severity, SeverityCounts, Vulnerability, WorkspaceScan,
+};
+
+const SCAN_COLUMNS: &str = "s.id, s.workspace_path, s.user_id, s.status, s.triggered_by, \
+     s.scanners, s.error, s.started_at, s.finished_at, \
+     (SELECT COUNT(*) FROM workspace_vulnerabilities v WHERE v.scan_id = s.id AND v.severity = 'critical') AS critical, \
+     (SELECT COUNT(*) FROM workspace_vulnerabilities v WHERE v.scan_id = s.id AND v.severity = 'high') AS high, \
+     (SELECT COUNT(*) FROM workspace_vulnerabilities v WHERE v.scan_id = s.id AND v.severity = 'medium') AS medium, \
+     (SELECT COUNT(*) FROM workspace_vulnerabilities v WHERE v.scan_id = s.id AND v.severity = 'low') AS low, \
+     (SELECT COUNT(*) FROM workspace_vulnerabilities v WHERE v.scan_id = s.id AND v.severity = 'unknown') AS unknown";
+
+const VULNERABILITY_COLUMNS: &str = "ecosystem, package, version, advisory_id, scanners, aliases, \
+     severity, cvss_score, summary, fixed_version, source_path, first_seen_at";
+
+#[derive(Debug, Clone, FromRow)]
+struct ScanRow {
+    id: String,
+    workspace_path: String,
+    user_id: Option<String>,
+    status: ScanStatus,
+    triggered_by: ScanTrigger,
+    scanners: String,
+    error: Option<String>,
+    started_at: String,
+    finished_at: Option<String>,
+    critical: i64,
+    high: i64,
+    medium: i64,
+    low: i64,
+    unknown: i64,
+}
+
+impl From<ScanRow> for WorkspaceScan {
+    fn from(row: ScanRow) -> Self {
+        Self {
+            id: row.id,
+            workspace_path: row.workspace_path,
+            user_id: row.user_id,
+            status: row.status,
+            triggered_by: row.triggered_by,
+            scanners: serde_json::from_str(&row.scanners).unwrap_or_default(),
+            error: row.error,
+            started_at: row.started_at,
+            finished_at: row.finished_at,
+            counts: SeverityCounts {
+                critical: row.critical,
+                high: row.high,
+                medium: row.medium,
+                low: row.low,
+                unknown: row.unknown,
+            },
+        }
+    }
+}
+
+#[derive(Debug, Clone, FromRow)]
+struct VulnerabilityRow {
+    ecosystem: String,
+    package: String,
+    version: String,
+    advisory_id: String,
+    scanners: String,
+    aliases: String,
+    severity: Severity,
+    cvss_score: Option<f64>,
+    summary: Option<String>,
+    fixed_version: Option<String>,
+    source_path: Option<String>,
+    first_seen_at: String,
+}
+
+impl From<VulnerabilityRow> for Vulnerability {
+    fn from(row: VulnerabilityRow) -> Self {
+        Self {
+            ecosystem: row.ecosystem,
+            package: row.package,
+            version: row.version,
+            advisory_id: row.advisory_id,
+            aliases: serde_json::from_str(&row.aliases).unwrap_or_default(),
+            scanners: serde_json::from_str(&row.scanners).unwrap_or_default(),
+            severity: row.severity,
+            cvss_score: row.cvss_score,
+            summary: row.summary,
+            fixed_version: row.fixed_version,
+            source_path: row.source_path,
+            first_seen_at: row.first_seen_at,
+        }
+    }
+}
+
+/// Identity of a finding across scans.
+type FindingKey = (String, String, String, String);
+
+fn finding_key(v: &Vulnerability) -> FindingKey {
+    (
+        v.ecosystem.clone(),
+        v.package.clone(),
+        v.version.clone(),
+        v.advisory_id.clone(),
+    )
+}
+
+#[derive(Debug, Clone)]
+pub struct VulnScanRepository {
+    pool: SqlitePool,
+}
+
+impl VulnScanRepository {
+    pub fn new(pool: SqlitePool) -> Self {
+        Self { pool }
+    }
+
+    fn generate_id() -> String {
+        format!("vsc_{}", nanoid::nanoid!(12))
+    }
+
+    /// Mark a workspace's scan as running, creating its row on first use.
+    /// Returns None if a scan of the workspace is already running.
+    pub async fn begin(
+        &self,
+        workspace_path: &str,
+        user_id: &str,
+        trigger: ScanTrigger,
+    ) -> Result<Option<WorkspaceScan>> {
+        let result = sqlx::query(
+            r#"INSERT INTO workspace_vuln_scans (id, workspace_path, user_id, status, triggered_by)
+               VALUES (?, ?, ?, 'running', ?)
+               ON CONFLICT(workspace_path) DO UPDATE SET
+                   user_id = excluded.user_id,
+                   status = 'running',
+                   triggered_by = excluded.triggered_by,
+                   error = NULL,
+                   started_at = datetime('now'),
+                   finished_at = NULL
+               WHERE workspace_vuln_scans.status != 'running'"#,
+        )
+        .bind(Self::generate_id())
+        .bind(workspace_path)
+        .bind(user_id)
+        .bind(trigger)
+        .execute(&self.pool)
+        .await
+        .context("begin vulnerability scan")?;
+        if result.rows_affected() == 0 {
+            return Ok(None);
+        }
+        self.get_by_path(workspace_path).await
+    }
+
+    pub async fn get(&self, id: &str) -> Result<Option<WorkspaceScan>> {
+        let row = sqlx::query_as::<_, ScanRow>(&format!(
+            "SELECT {SCAN_COLUMNS} FROM workspace_vuln_scans s WHERE s.id = ?"
+        ))
+        .bind(id)
+        .fetch_optional(&self.pool)
+        .await
+        .context("get vulnerability scan")?;
+        Ok(row.map(Into::into))
+    }
+
+    pub async fn get_by_path(&self, workspace_path: &str) -> Result<Option<WorkspaceScan>> {
+        let row = sqlx::query_as::<_, ScanRow>(&format!(
+            "SELECT {SCAN_COLUMNS} FROM workspace_vuln_scans s WHERE s.workspace_path = ?"
+        ))
+        .bind(workspace_path)
+        .fetch_optional(&self.pool)
+        .await
+        .context("get vulnerability scan by path")?;
+        Ok(row.map(Into::into))
+    }
+
+    /// Scans last triggered by a user, most recent first.
+    pub async fn list_for_user(&self, user_id: &str) -> Result<Vec<WorkspaceScan>> {
+        let rows = sqlx::query_as::<_, ScanRow>(&format!(
+            "SELECT {SCAN_COLUMNS} FROM workspace_vuln_scans s WHERE s.user_id = ? \
+             ORDER BY s.started_at DESC, s.id DESC"
+        ))
+        .bind(user_id)
+        .fetch_all(&self.pool)
+        .await
+        .context("list vulnerability scans")?;
+        Ok(rows.into_iter().map(Into::into).collect())
+    }
+
+    /// Findings of a scan, most severe first.
+    pub async fn vulnerabilities(&self, scan_id: &str) -> Result<Vec<Vulnerability>> {
+        let rows = sqlx::query_as::<_, VulnerabilityRow>(&format!(
+            "SELECT {VULNERABILITY_COLUMNS} FROM workspace_vulnerabilities WHERE scan_id = ? \
+             ORDER BY CASE severity WHEN 'critical' THEN 0 WHEN 'high' THEN 1 \
+             WHEN 'medium' THEN 2 WHEN 'low' THEN 3 ELSE 4 END, package, advisory_id"
+        ))
+        .bind(scan_id)
+        .fetch_all(&self.pool)
+        .await
+        .context("list workspace vulnerabilities")?;
+        Ok(rows.into_iter().map(Into::into).collect())
+    }
+
+    /// Finish a scan, replacing its findings. Findings seen by an earlier
+    /// scan keep their `first_seen_at`; the ones seen for the first time
+    /// are returned.
+    pub async fn complete(
+        &self,
+        scan_id: &str,
+        scanners: &[ScannerOutcome],
+        findings: &[Vulnerability],
+    ) -> Result<Vec<Vulnerability>> {
+        let previous: HashMap<FindingKey, String> = self
+            .vulnerabilities(scan_id)
+            .await?
+            .into_iter()
+            .map(|v| (finding_key(&v), v.first_seen_at))
+            .collect();
+
+        let mut tx = self.pool.begin().await.context("begin transaction")?;
+        sqlx::query("DELETE FROM workspace_vulnerabilities WHERE scan_id = ?")
+            .bind(scan_id)
+            .execute(&mut *tx)
+            .await
+            .context("clear workspace vulnerabilities")?;
+        let mut new = Vec::new();
+        for finding in findings {
+            let first_seen_at = previous.get(&finding_key(finding));
+            sqlx::query(
+                r#"INSERT OR IGNORE INTO workspace_vulnerabilities
+                       (scan_id, ecosystem, package, version, advisory_id, scanners, aliases,
+                        severity, cvss_score, summary, fixed_version, source_path, first_seen_at)
+                   VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, COALESCE(?, datetime('now')))"#,
+            )
+            .bind(scan_id)
+            .bind(&finding.ecosystem)
+            .bind(&finding.package)
+            .bind(&finding.version)
+            .bind(&finding.advisory_id)
+            .bind(serde_json::to_string(&finding.scanners).unwrap_or_else(|_| "[]".to_string()))
+            .bind(serde_json::to_string(&finding.aliases).unwrap_or_else(|_| "[]".to_string()))
+            .bind(finding.severity)
+            .bind(finding.cvss_score)
+            .bind(&finding.summary)
+            .bind(&finding.fixed_version)
+            .bind(&finding.source_path)
+            .bind(first_seen_at)
+            .execute(&mut *tx)
+            .await
+            .context("insert workspace vulnerability")?;
+            if first_seen_at.is_none() {
+                new.push(finding.clone());
+            }
+        }
+        sqlx::query(
+            r#"UPDATE workspace_vuln_scans
+               SET status = 'completed', scanners = ?, error = NULL, finished_at = datetime('now')
+               WHERE id = ?"#,
+        )
+        .bind(serde_json::to_string(scanners).unwrap_or_else(|_| "[]".to_string()))
+        .bind(scan_id)
+        .execute(&mut *tx)
+        .await
+        .context("complete vulnerability scan")?;
+        tx.commit().await.context("commit vulnerability scan")?;
+        Ok(new)
+    }
+
+    /// Mark a scan as failed. Findings of the previous scan are kept.
+    pub async fn fail(
+        &self,
+        scan_id: &str,
+        scanners: &[ScannerOutcome],
+        error: &str,
+    ) -> Result<()> {
+        sqlx::query(
+            r#"UPDATE workspace_vuln_scans
+               SET status = 'failed', scanners = ?, error = ?, finished_at = datetime('now')
+               WHERE id = ?"#,
+        )
+        .bind(serde_json::to_string(scanners).unwrap_or_else(|_| "[]".to_string()))
+        .bind(error)
+        .bind(scan_id)
+        .execute(&self.pool)
+        .await
+        .context("fail vulnerability scan")?;
+        Ok(())
+    }
+
+    /// Fail scans left running by a previous backend process.
+    pub async fn fail_interrupted(&self) -> Result<u64> {
+        let result = sqlx::query(
+            r#"UPDATE workspace_vuln_scans
+               SET status = 'failed', error = 'interrupted by backend restart',
+                   finished_at = datetime('now')
+               WHERE status = 'running'"#,
+        )
+        .execute(&self.pool)
+        .await
+        .context("fail interrupted vulnerability scans")?;
+        Ok(result.rows_affected())
+    }
+
+    /// Seconds since the workspace's latest scan started, if it has one.
+    pub async fn seconds_since_last_scan(&self, workspace_path: &str) -> Result<Option<i64>> {
+        let row = sqlx::query_as::<_, (i64,)>(
+            r#"SELECT CAST(strftime('%s', 'now') - strftime('%s', started_at) AS INTEGER)
+               FROM workspace_vuln_scans WHERE workspace_path = ?"#,
+        )
+        .bind(workspace_path)
+        .fetch_optional(&self.pool)
+        .await
+        .context("get last vulnerability scan time")?;
+        Ok(row.map(|(secs,)| secs))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::Database;
+    use oqto_runner::protocol::DependencyScanner;
+
+    async fn repo() -> VulnScanRepository {
+        let db = Database::in_memory().await.unwrap();
+        sqlx::query("INSERT INTO users (id, username, email, display_name) VALUES (?, ?, ?, ?)")
+            .bind("alice")
+            .bind("alice")
+            .bind("alice@example.com")
+            .bind("Alice")
+            .execute(db.pool())
+            .await
+            .unwrap();
+        VulnScanRepository::new(db.pool().clone())
+    }
+
+    fn finding(package: &str, advisory_id: &str, severity: Severity) -> Vulnerability {
+        Vulnerability {
+            ecosystem: "npm".to_string(),
+            package: package.to_string(),
+            version: "1.0.0".to_string(),
+            advisory_id: advisory_id.to_string(),
+            aliases: vec!["CVE-2024-0001".to_string()],
+            scanners: vec![DependencyScanner::NpmAudit],
+            severity,
+            cvss_score: None,
+            summary: Some("bad".to_string()),
+            fixed_version: None,
+            source_path: Some("package-lock.json".to_string()),
+            first_seen_at: String::new(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_scan_lifecycle_tracks_new_findings() {
+        let repo = repo().await;
+        let ws = "/home/alice/app";
+        let scan = repo
+            .begin(ws, "alice", ScanTrigger::FileChange)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(scan.status, ScanStatus::Running);
+        // Only one scan per workspace at a time.
+        assert!(
+            repo.begin(ws, "alice", ScanTrigger::Manual)
+                .await
+                .unwrap()
+                .is_none()
+        );
+
+        let first = [
+            finding("minimist", "GHSA-1", Severity::Critical),
+            finding("lodash", "GHSA-2", Severity::Low),
+        ];
+        let new = repo.complete(&scan.id, &[], &first).await.unwrap();
+        assert_eq!(new.len(), 2);
+
+        let done = repo.get(&scan.id).await.unwrap().unwrap();
+        assert_eq!(done.status, ScanStatus::Completed);
+        assert_eq!(done.counts.critical, 1);
+        assert_eq!(done.counts.low, 1);
+        let stored = repo.vulnerabilities(&scan.id).await.unwrap();
+        assert_eq!(stored[0].package, "minimist");
+        assert_eq!(stored[0].aliases, ["CVE-2024-0001"]);
+
+        // A re-scan keeps the row id and only reports the new finding.
+        let again = repo
+            .begin(ws, "alice", ScanTrigger::Manual)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(again.id, scan.id);
+        let second = [
+            finding("minimist", "GHSA-1", Severity::Critical),
+            finding("axios", "GHSA-3", Severity::High),
+        ];
+        let new = repo.complete(&scan.id, &[], &second).await.unwrap();
+        assert_eq!(new.len(), 1);
+        assert_eq!(new[0].package, "axios");
+        assert_eq!(repo.vulnerabilities(&scan.id).await.unwrap().len(), 2);
+
+        assert_eq!(repo.list_for_user("alice").await.unwrap().len(), 1);
+        assert!(
+            repo.seconds_since_last_scan(ws)
+                .await
+                .unwrap()
+                .is_some_and(|s| s < 60)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_interrupted_scans_fail() {
+        let repo = repo().await;
+        let scan = repo
+            .begin("/home/alice/app", "alice", ScanTrigger::Manual)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(repo.fail_interrupted().await.unwrap(), 1);
+        let failed = repo.get(&scan.id).await.unwrap().unwrap();
+        assert_eq!(failed.status, ScanStatus::Failed);
+        assert!(failed.error.is_some());
+    }
+}
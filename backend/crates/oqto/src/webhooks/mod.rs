@@ -0,0 +1,456 @@
+//! Outbound webhooks for session lifecycle events.
+//!
+//! Each `[[webhooks]]` entry names a URL and the events it wants. When a
+//! session is created, started, stopped or fails, an agent run completes or
+//! a session exceeds its budget, the event is written to the delivery log
+//! once per interested webhook and POSTed from there as JSON:
+//!
+//! ```json
+//! { "id": "evt_…", "event": "session.failed", "created_at": "…", "data": { … } }
+//! ```
+//!
+//! With a `secret`, the request carries `X-Oqto-Signature: sha256=<hex>`, the
+//! HMAC-SHA256 of `<X-Oqto-Timestamp>.<body>` under the secret, so receivers
+//! can check origin and reject replays. Failed attempts (no answer or a
+//! non-2xx status) are retried with exponential backoff; after
+//! [`MAX_ATTEMPTS`] the delivery is marked failed. Admins see the log, and
+//! can queue failed deliveries again, under `/api/admin/webhooks`.
+
+mod models;
+mod repository;
+
+pub use models::{
+    DeliveryListQuery, DeliveryStatus, WebhookConfig, WebhookDelivery, WebhookEvent, WebhookInfo,
+};
+pub use repository::{NewWebhookDelivery, WebhookDeliveryRepository};
+
+use std::collections::{HashSet, VecDeque};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use anyhow::{Context, Result, bail};
+use hmac::{Hmac, Mac};
+use oqto_protocol::events::EventPayload;
+use oqto_runner::client::{PiSubscriptionEvent, RunnerClient};
+use sha2::Sha256;
+use tokio::sync::Notify;
+use tracing::{info, warn};
+
+/// Attempts per delivery before it is marked failed.
+pub const MAX_ATTEMPTS: i64 = 8;
+/// Delay before the first retry; doubles with every further attempt.
+const RETRY_BASE: Duration = Duration::from_secs(10);
+const RETRY_MAX: Duration = Duration::from_secs(3600);
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+/// How often the log is checked for due retries when nothing new comes in.
+const POLL_INTERVAL: Duration = Duration::from_secs(15);
+/// Most deliveries attempted at once.
+const BATCH_SIZE: i64 = 50;
+/// Finished deliveries are kept this long.
+const RETENTION: chrono::Duration = chrono::Duration::days(30);
+const PRUNE_INTERVAL: Duration = Duration::from_secs(3600);
+/// Announced runs remembered so none is announced twice.
+const RECENT_RUNS: usize = 1024;
+
+/// `sha256=<hex>` signature of a delivery body sent at `timestamp`.
+pub fn sign(secret: &str, timestamp: i64, body: &str) -> String {
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC accepts any key length");
+    mac.update(timestamp.to_string().as_bytes());
+    mac.update(b".");
+    mac.update(body.as_bytes());
+    format!("sha256={}", hex::encode(mac.finalize().into_bytes()))
+}
+
+/// Delay after the `attempts`th failed attempt, or None when the delivery
+/// has run out of attempts.
+fn retry_delay(attempts: i64) -> Option<Duration> {
+    if attempts >= MAX_ATTEMPTS {
+        return None;
+    }
+    let exponent = attempts.saturating_sub(1).clamp(0, 16) as u32;
+    Some(RETRY_BASE.saturating_mul(2u32.pow(exponent)).min(RETRY_MAX))
+}
+
+/// Name of a webhook in the delivery log.
+fn webhook_name(config: &WebhookConfig, url: &reqwest::Url) -> String {
+    config
+        .name
+        .clone()
+        .filter(|name| !name.trim().is_empty())
+        .unwrap_or_else(|| url.host_str().unwrap_or(config.url.as_str()).to_string())
+}
+
+struct Webhook {
+    name: String,
+    config: WebhookConfig,
+}
+
+/// Queues events for the configured webhooks and delivers them.
+pub struct WebhookService {
+    hooks: Vec<Webhook>,
+    repo: WebhookDeliveryRepository,
+    client: reqwest::Client,
+    wake: Notify,
+    /// Sessions whose runner events are watched for finished runs.
+    watched_sessions: Mutex<HashSet<String>>,
+    /// Runs already announced, oldest first.
+    runs_completed: Mutex<VecDeque<String>>,
+}
+
+impl WebhookService {
+    pub fn new(configs: Vec<WebhookConfig>, repo: WebhookDeliveryRepository) -> Result<Self> {
+        let mut hooks: Vec<Webhook> = Vec::with_capacity(configs.len());
+        for config in configs {
+            let url = reqwest::Url::parse(&config.url)
+                .with_context(|| format!("invalid webhook url {:?}", config.url))?;
+            if !matches!(url.scheme(), "http" | "https") {
+                bail!("webhook url {:?} must be http or https", config.url);
+            }
+            let name = webhook_name(&config, &url);
+            if hooks.iter().any(|hook| hook.name == name) {
+                bail!("two webhooks are named {name:?}; set distinct names");
+            }
+            hooks.push(Webhook { name, config });
+        }
+        let client = reqwest::Client::builder()
+            .timeout(REQUEST_TIMEOUT)
+            .build()
+            .context("building webhook HTTP client")?;
+        Ok(Self {
+            hooks,
+            repo,
+            client,
+            wake: Notify::new(),
+            watched_sessions: Mutex::new(HashSet::new()),
+            runs_completed: Mutex::new(VecDeque::new()),
+        })
+    }
+
+    pub fn repo(&self) -> &WebhookDeliveryRepository {
+        &self.repo
+    }
+
+    /// The configured webhooks, without their secrets.
+    pub fn webhooks(&self) -> Vec<WebhookInfo> {
+        self.hooks
+            .iter()
+            .map(|hook| WebhookInfo {
+                name: hook.name.clone(),
+                url: hook.config.url.clone(),
+                events: hook.config.events.clone(),
+                signed: hook.config.secret.is_some(),
+            })
+            .collect()
+    }
+
+    /// Queue `event` for every webhook that wants it.
+    pub async fn emit(&self, event: WebhookEvent, data: serde_json::Value) {
+        let hooks: Vec<&Webhook> = self
+            .hooks
+            .iter()
+            .filter(|hook| hook.config.wants(event))
+            .collect();
+        if hooks.is_empty() {
+            return;
+        }
+        let payload = serde_json::json!({
+            "id": format!("evt_{}", nanoid::nanoid!()),
+            "event": event.as_str(),
+            "created_at": chrono::Utc::now().to_rfc3339(),
+            "data": data,
+        })
+        .to_string();
+        for hook in hooks {
+            let queued = self
+                .repo
+                .enqueue(NewWebhookDelivery {
+                    webhook: &hook.name,
+                    url: &hook.config.url,
+                    event: event.as_str(),
+                    payload: &payload,
+                })
+                .await;
+            if let Err(e) = queued {
+                warn!(
+                    webhook = %hook.name,
+                    event = event.as_str(),
+                    "Failed to queue webhook delivery: {e:#}"
+                );
+            }
+        }
+        self.wake.notify_one();
+    }
+
+    /// Watch a session's events on its runner and queue
+    /// `agent.run_completed` for every run the runner reports finished,
+    /// until the session closes, whether or not a client is connected.
+    /// `data` describes the session; each event adds `session_id` and the
+    /// run's `run_id`. Returns once the subscription is in place, so runs
+    /// prompted afterwards are seen. Sessions already watched are left alone.
+    pub async fn watch_runs(
+        self: &Arc<Self>,
+        runner: &RunnerClient,
+        session_id: &str,
+        data: serde_json::Value,
+    ) {
+        if !self
+            .hooks
+            .iter()
+            .any(|hook| hook.config.wants(WebhookEvent::AgentRunCompleted))
+            || !self
+                .watched_sessions
+                .lock()
+                .unwrap()
+                .insert(session_id.to_string())
+        {
+            return;
+        }
+        let mut subscription = match runner.agent_subscribe(session_id).await {
+            Ok(subscription) => subscription,
+            Err(e) => {
+                self.watched_sessions.lock().unwrap().remove(session_id);
+                warn!(session_id = %session_id, "Failed to watch agent runs: {e:#}");
+                return;
+            }
+        };
+        let service = Arc::clone(self);
+        let session_id = session_id.to_string();
+        tokio::spawn(async move {
+            while let Some(PiSubscriptionEvent::Event(event)) = subscription.next().await {
+                if let EventPayload::AgentRunCompleted { run_id } = &event.payload {
+                    let mut data = data.clone();
+                    if let Some(fields) = data.as_object_mut() {
+                        fields.insert("session_id".into(), event.session_id.clone().into());
+                        fields.insert("run_id".into(), run_id.clone().into());
+                    }
+                    service.agent_run_completed(run_id, data).await;
+                }
+            }
+            service.watched_sessions.lock().unwrap().remove(&session_id);
+        });
+    }
+
+    /// Queue `agent.run_completed` for a run, once however often it is
+    /// reported.
+    async fn agent_run_completed(&self, run_id: &str, data: serde_json::Value) {
+        {
+            let mut runs = self.runs_completed.lock().unwrap();
+            if runs.iter().any(|run| run == run_id) {
+                return;
+            }
+            if runs.len() >= RECENT_RUNS {
+                runs.pop_front();
+            }
+            runs.push_back(run_id.to_string());
+        }
+        self.emit(WebhookEvent::AgentRunCompleted, data).await;
+    }
+
+    /// Delivery loop: attempt due deliveries as they are queued and their
+    /// retries come due. Runs until shutdown.
+    pub async fn run(self: Arc<Self>) {
+        let mut last_prune: Option<Instant> = None;
+        loop {
+            if last_prune.is_none_or(|at| at.elapsed() >= PRUNE_INTERVAL) {
+                last_prune = Some(Instant::now());
+                let before = crate::db::timestamp(chrono::Utc::now() - RETENTION);
+                match self.repo.prune(&before).await {
+                    Ok(0) => {}
+                    Ok(pruned) => info!("Pruned {pruned} old webhook deliveries"),
+                    Err(e) => warn!("Failed to prune webhook deliveries: {e:#}"),
+                }
+            }
+            match self.repo.due(&crate::db::now(), BATCH_SIZE).await {
+                Ok(due) if !due.is_empty() => {
+                    let full = due.len() as i64 >= BATCH_SIZE;
+                    futures::future::join_all(due.iter().map(|d| self.attempt(d))).await;
+                    if full {
+                        continue;
+                    }
+                }
+                Ok(_) => {}
+                Err(e) => warn!("Failed to list due webhook deliveries: {e:#}"),
+            }
+            tokio::select! {
+                _ = self.wake.notified() => {}
+                _ = tokio::time::sleep(POLL_INTERVAL) => {}
+            }
+        }
+    }
+
+    /// Make one attempt at a delivery and record the outcome.
+    async fn attempt(&self, delivery: &WebhookDelivery) {
+        let Some(hook) = self.hooks.iter().find(|hook| hook.name == delivery.webhook) else {
+            let error = "webhook is no longer configured";
+            if let Err(e) = self
+                .repo
+                .mark_attempt_failed(&delivery.id, None, error, None)
+                .await
+            {
+                warn!(delivery_id = %delivery.id, "Failed to record webhook delivery: {e:#}");
+            }
+            return;
+        };
+
+        let body = delivery.payload.to_string();
+        let timestamp = chrono::Utc::now().timestamp();
+        let mut request = self
+            .client
+            .post(&delivery.url)
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .header("X-Oqto-Event", &delivery.event)
+            .header("X-Oqto-Delivery", &delivery.id)
+            .header("X-Oqto-Timestamp", timestamp.to_string());
+        if let Some(secret) = &hook.config.secret {
+            request = request.header("X-Oqto-Signature", sign(secret, timestamp, &body));
+        }
+        let (status_code, error) = match request.body(body).send().await {
+            Ok(response) if response.status().is_success() => {
+                let recorded = self
+                    .repo
+                    .mark_delivered(&delivery.id, i64::from(response.status().as_u16()))
+                    .await;
+                if let Err(e) = recorded {
+                    warn!(delivery_id = %delivery.id, "Failed to record webhook delivery: {e:#}");
+                }
+                return;
+            }
+            Ok(response) => (
+                Some(i64::from(response.status().as_u16())),
+                format!("HTTP {}", response.status()),
+            ),
+            Err(e) => (None, format!("{e:#}")),
+        };
+
+        let attempts = delivery.attempts + 1;
+        let next_attempt_at = retry_delay(attempts).map(|delay| {
+            crate::db::timestamp(
+                chrono::Utc::now() + chrono::Duration::from_std(delay).unwrap_or_default(),
+            )
+        });
+        if next_attempt_at.is_none() {
+            warn!(
+                webhook = %hook.name,
+                delivery_id = %delivery.id,
+                event = %delivery.event,
+                "Webhook delivery failed after {attempts} attempts: {error}"
+            );
+        }
+        if let Err(e) = self
+            .repo
+            .mark_attempt_failed(
+                &delivery.id,
+                status_code,
+                &error,
+                next_attempt_at.as_deref(),
+            )
+            .await
+        {
+            warn!(delivery_id = %delivery.id, "Failed to record webhook delivery: {e:#}");
+        }
+    }
+
+    /// Queue a failed delivery again. Returns false when there is no failed
+    /// delivery `id`.
+    pub async fn retry(&self, id: &str) -> Result<bool> {
+        let queued = self.repo.retry(id).await?;
+        if queued {
+            self.wake.notify_one();
+        }
+        Ok(queued)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn signatures_and_retry_delays() {
+        let signature = sign("s3cret", 1_700_000_000, r#"{"event":"session.created"}"#);
+        assert!(signature.starts_with("sha256="));
+        assert_eq!(signature.len(), "sha256=".len() + 64);
+        assert_eq!(
+            signature,
+            sign("s3cret", 1_700_000_000, r#"{"event":"session.created"}"#)
+        );
+        assert_ne!(
+            signature,
+            sign("s3cret", 1_700_000_001, r#"{"event":"session.created"}"#)
+        );
+        assert_ne!(
+            signature,
+            sign("other", 1_700_000_000, r#"{"event":"session.created"}"#)
+        );
+
+        assert_eq!(retry_delay(1), Some(Duration::from_secs(10)));
+        assert_eq!(retry_delay(2), Some(Duration::from_secs(20)));
+        assert_eq!(retry_delay(4), Some(Duration::from_secs(80)));
+        assert_eq!(
+            retry_delay(MAX_ATTEMPTS - 1),
+            Some(Duration::from_secs(640))
+        );
+        assert_eq!(retry_delay(MAX_ATTEMPTS), None);
+    }
+
+    #[test]
+    fn webhooks_filter_events_and_need_http_urls() {
+        let config = |url: &str, events| WebhookConfig {
+            name: None,
+            url: url.to_string(),
+            events,
+            secret: None,
+        };
+        let hook = config(
+            "https://ci.example.com/hook",
+            vec![WebhookEvent::SessionFailed],
+        );
+        assert!(hook.wants(WebhookEvent::SessionFailed));
+        assert!(!hook.wants(WebhookEvent::SessionCreated));
+        assert!(config("https://x.example.com", Vec::new()).wants(WebhookEvent::BudgetExceeded));
+
+        let parsed: WebhookConfig = toml::from_str(
+            "url = \"https://ci.example.com/hook\"\nevents = [\"agent.run_completed\", \"budget.exceeded\"]",
+        )
+        .unwrap();
+        assert_eq!(
+            parsed.events,
+            vec![
+                WebhookEvent::AgentRunCompleted,
+                WebhookEvent::BudgetExceeded
+            ]
+        );
+        assert_eq!(
+            webhook_name(&parsed, &reqwest::Url::parse(&parsed.url).unwrap()),
+            "ci.example.com"
+        );
+    }
+
+    #[tokio::test]
+    async fn runs_are_announced_once_per_run_id() {
+        let db = crate::db::Database::in_memory().await.unwrap();
+        let service = WebhookService::new(
+            vec![WebhookConfig {
+                name: Some("ci".to_string()),
+                url: "https://ci.example.com/hook".to_string(),
+                events: vec![WebhookEvent::AgentRunCompleted],
+                secret: None,
+            }],
+            WebhookDeliveryRepository::new(db.shared().clone()),
+        )
+        .unwrap();
+        let data = serde_json::json!({"session_id": "ses_1"});
+        service.agent_run_completed("run_a", data.clone()).await;
+        service.agent_run_completed("run_a", data.clone()).await;
+        // A run right after another is announced too.
+        service.agent_run_completed("run_b", data).await;
+
+        let deliveries = service
+            .repo()
+            .list(&DeliveryListQuery::default())
+            .await
+            .unwrap();
+        assert_eq!(deliveries.len(), 2);
+    }
+}
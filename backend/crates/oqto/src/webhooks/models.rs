@@ -0,0 +1,127 @@
+use serde::{Deserialize, Serialize};
+
+/// Events a webhook can subscribe to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum WebhookEvent {
+    #[serde(rename = "session.created")]
+    SessionCreated,
+    #[serde(rename = "session.started")]
+    SessionStarted,
+    /// Stopped or hibernated.
+    #[serde(rename = "session.stopped")]
+    SessionStopped,
+    #[serde(rename = "session.failed")]
+    SessionFailed,
+    /// The agent finished working on a prompt and went idle.
+    #[serde(rename = "agent.run_completed")]
+    AgentRunCompleted,
+    /// A session spent its whole budget.
+    #[serde(rename = "budget.exceeded")]
+    BudgetExceeded,
+}
+
+impl WebhookEvent {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            WebhookEvent::SessionCreated => "session.created",
+            WebhookEvent::SessionStarted => "session.started",
+            WebhookEvent::SessionStopped => "session.stopped",
+            WebhookEvent::SessionFailed => "session.failed",
+            WebhookEvent::AgentRunCompleted => "agent.run_completed",
+            WebhookEvent::BudgetExceeded => "budget.exceeded",
+        }
+    }
+}
+
+/// An endpoint events are POSTed to (`[[webhooks]]`).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WebhookConfig {
+    /// Name in the delivery log (default: the URL's host).
+    #[serde(default)]
+    pub name: Option<String>,
+    /// `http` or `https` URL the events are POSTed to.
+    pub url: String,
+    /// Events to deliver; empty delivers all.
+    #[serde(default)]
+    pub events: Vec<WebhookEvent>,
+    /// Key of the `X-Oqto-Signature` HMAC; deliveries are unsigned without
+    /// one.
+    #[serde(default)]
+    pub secret: Option<String>,
+}
+
+impl WebhookConfig {
+    pub fn wants(&self, event: WebhookEvent) -> bool {
+        self.events.is_empty() || self.events.contains(&event)
+    }
+}
+
+/// A configured webhook as the admin API shows it, without its secret.
+#[derive(Debug, Clone, Serialize)]
+pub struct WebhookInfo {
+    pub name: String,
+    pub url: String,
+    pub events: Vec<WebhookEvent>,
+    pub signed: bool,
+}
+
+/// Delivery state of one event to one webhook.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DeliveryStatus {
+    /// Waiting for its first attempt or a retry.
+    Pending,
+    /// The endpoint answered with a 2xx status.
+    Delivered,
+    /// Every attempt failed.
+    Failed,
+}
+
+impl DeliveryStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            DeliveryStatus::Pending => "pending",
+            DeliveryStatus::Delivered => "delivered",
+            DeliveryStatus::Failed => "failed",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "pending" => Some(DeliveryStatus::Pending),
+            "delivered" => Some(DeliveryStatus::Delivered),
+            "failed" => Some(DeliveryStatus::Failed),
+            _ => None,
+        }
+    }
+}
+
+/// One event queued for, or delivered to, one webhook.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct WebhookDelivery {
+    pub id: String,
+    /// Name of the webhook.
+    pub webhook: String,
+    pub url: String,
+    pub event: String,
+    /// The JSON body that is POSTed.
+    pub payload: serde_json::Value,
+    pub status: DeliveryStatus,
+    pub attempts: i64,
+    /// HTTP status of the last attempt, when the endpoint answered.
+    pub last_status_code: Option<i64>,
+    pub last_error: Option<String>,
+    /// When the next attempt is due, while pending.
+    pub next_attempt_at: Option<String>,
+    pub created_at: String,
+    pub delivered_at: Option<String>,
+}
+
+/// Query of `GET /admin/webhooks/deliveries`.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct DeliveryListQuery {
+    pub webhook: Option<String>,
+    pub event: Option<String>,
+    pub status: Option<DeliveryStatus>,
+    pub limit: Option<i64>,
+}
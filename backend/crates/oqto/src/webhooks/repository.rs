@@ -0,0 +1,260 @@
+use anyhow::{Context, Result};
+use sqlx::FromRow;
+
+use crate::db::{self, DbPool, on_pool};
+
+use super::{DeliveryListQuery, DeliveryStatus, WebhookDelivery};
+
+const DELIVERY_COLUMNS: &str = "id, webhook, url, event, payload, status, attempts, \
+     last_status_code, last_error, next_attempt_at, created_at, delivered_at";
+
+/// Most deliveries in one list.
+const MAX_LIST: i64 = 500;
+
+#[derive(Debug, Clone, FromRow)]
+struct DeliveryRow {
+    id: String,
+    webhook: String,
+    url: String,
+    event: String,
+    payload: String,
+    status: String,
+    attempts: i64,
+    last_status_code: Option<i64>,
+    last_error: Option<String>,
+    next_attempt_at: Option<String>,
+    created_at: String,
+    delivered_at: Option<String>,
+}
+
+impl From<DeliveryRow> for WebhookDelivery {
+    fn from(row: DeliveryRow) -> Self {
+        Self {
+            id: row.id,
+            webhook: row.webhook,
+            url: row.url,
+            event: row.event,
+            payload: serde_json::from_str(&row.payload)
+                .unwrap_or(serde_json::Value::String(row.payload)),
+            // The column is constrained to the known values.
+            status: DeliveryStatus::parse(&row.status).unwrap_or(DeliveryStatus::Failed),
+            attempts: row.attempts,
+            last_status_code: row.last_status_code,
+            last_error: row.last_error,
+            next_attempt_at: row.next_attempt_at,
+            created_at: row.created_at,
+            delivered_at: row.delivered_at,
+        }
+    }
+}
+
+/// An event to queue for one webhook.
+#[derive(Debug, Clone)]
+pub struct NewWebhookDelivery<'a> {
+    pub webhook: &'a str,
+    pub url: &'a str,
+    pub event: &'a str,
+    pub payload: &'a str,
+}
+
+#[derive(Debug, Clone)]
+pub struct WebhookDeliveryRepository {
+    pool: DbPool,
+}
+
+impl WebhookDeliveryRepository {
+    pub fn new(pool: DbPool) -> Self {
+        Self { pool }
+    }
+
+    /// Queue a delivery, due right away. Returns its id.
+    pub async fn enqueue(&self, delivery: NewWebhookDelivery<'_>) -> Result<String> {
+        let id = format!("whd_{}", nanoid::nanoid!());
+        let now = db::now();
+        on_pool!(&self.pool, |pool| sqlx::query(
+            "INSERT INTO webhook_deliveries (id, webhook, url, event, payload, status, attempts, \
+             next_attempt_at, created_at) VALUES ($1, $2, $3, $4, $5, 'pending', 0, $6, $6)"
+        )
+        .bind(&id)
+        .bind(delivery.webhook)
+        .bind(delivery.url)
+        .bind(delivery.event)
+        .bind(delivery.payload)
+        .bind(&now)
+        .execute(pool)
+        .await
+        .map(|_| ()))
+        .context("queue webhook delivery")?;
+        Ok(id)
+    }
+
+    pub async fn get(&self, id: &str) -> Result<Option<WebhookDelivery>> {
+        let sql = format!("SELECT {DELIVERY_COLUMNS} FROM webhook_deliveries WHERE id = $1");
+        let row: Option<DeliveryRow> = on_pool!(&self.pool, |pool| sqlx::query_as(&sql)
+            .bind(id)
+            .fetch_optional(pool)
+            .await)
+        .context("get webhook delivery")?;
+        Ok(row.map(WebhookDelivery::from))
+    }
+
+    /// Pending deliveries due at `now`, longest waiting first.
+    pub async fn due(&self, now: &str, limit: i64) -> Result<Vec<WebhookDelivery>> {
+        let sql = format!(
+            "SELECT {DELIVERY_COLUMNS} FROM webhook_deliveries \
+             WHERE status = 'pending' AND next_attempt_at <= $1 \
+             ORDER BY next_attempt_at, created_at LIMIT $2"
+        );
+        let rows: Vec<DeliveryRow> = on_pool!(&self.pool, |pool| sqlx::query_as(&sql)
+            .bind(now)
+            .bind(limit)
+            .fetch_all(pool)
+            .await)
+        .context("list due webhook deliveries")?;
+        Ok(rows.into_iter().map(WebhookDelivery::from).collect())
+    }
+
+    /// Record a successful attempt.
+    pub async fn mark_delivered(&self, id: &str, status_code: i64) -> Result<()> {
+        on_pool!(&self.pool, |pool| sqlx::query(
+            "UPDATE webhook_deliveries SET status = 'delivered', attempts = attempts + 1, \
+             last_status_code = $1, last_error = NULL, next_attempt_at = NULL, delivered_at = $2 \
+             WHERE id = $3"
+        )
+        .bind(status_code)
+        .bind(db::now())
+        .bind(id)
+        .execute(pool)
+        .await
+        .map(|_| ()))
+        .context("mark webhook delivery delivered")?;
+        Ok(())
+    }
+
+    /// Record a failed attempt: the delivery stays pending until
+    /// `next_attempt_at`, or fails for good without one.
+    pub async fn mark_attempt_failed(
+        &self,
+        id: &str,
+        status_code: Option<i64>,
+        error: &str,
+        next_attempt_at: Option<&str>,
+    ) -> Result<()> {
+        let status = match next_attempt_at {
+            Some(_) => DeliveryStatus::Pending,
+            None => DeliveryStatus::Failed,
+        };
+        on_pool!(&self.pool, |pool| sqlx::query(
+            "UPDATE webhook_deliveries SET status = $1, attempts = attempts + 1, \
+             last_status_code = $2, last_error = $3, next_attempt_at = $4 WHERE id = $5"
+        )
+        .bind(status.as_str())
+        .bind(status_code)
+        .bind(error)
+        .bind(next_attempt_at)
+        .bind(id)
+        .execute(pool)
+        .await
+        .map(|_| ()))
+        .context("record failed webhook delivery")?;
+        Ok(())
+    }
+
+    /// Queue a failed delivery again with a fresh set of attempts. Returns
+    /// false when there is no failed delivery `id`.
+    pub async fn retry(&self, id: &str) -> Result<bool> {
+        let rows = on_pool!(&self.pool, |pool| sqlx::query(
+            "UPDATE webhook_deliveries SET status = 'pending', attempts = 0, \
+             next_attempt_at = $1 WHERE id = $2 AND status = 'failed'"
+        )
+        .bind(db::now())
+        .bind(id)
+        .execute(pool)
+        .await
+        .map(|r| r.rows_affected()))
+        .context("retry webhook delivery")?;
+        Ok(rows > 0)
+    }
+
+    /// Deliveries, newest first.
+    pub async fn list(&self, query: &DeliveryListQuery) -> Result<Vec<WebhookDelivery>> {
+        let limit = query.limit.unwrap_or(100).clamp(1, MAX_LIST);
+        let sql = format!(
+            "SELECT {DELIVERY_COLUMNS} FROM webhook_deliveries \
+             WHERE ($1 IS NULL OR webhook = $1) AND ($2 IS NULL OR event = $2) \
+             AND ($3 IS NULL OR status = $3) \
+             ORDER BY created_at DESC, id DESC LIMIT $4"
+        );
+        let rows: Vec<DeliveryRow> = on_pool!(&self.pool, |pool| sqlx::query_as(&sql)
+            .bind(query.webhook.as_deref())
+            .bind(query.event.as_deref())
+            .bind(query.status.map(|s| s.as_str()))
+            .bind(limit)
+            .fetch_all(pool)
+            .await)
+        .context("list webhook deliveries")?;
+        Ok(rows.into_iter().map(WebhookDelivery::from).collect())
+    }
+
+    /// Delete finished deliveries created before `before`.
+    pub async fn prune(&self, before: &str) -> Result<u64> {
+        on_pool!(&self.pool, |pool| sqlx::query(
+            "DELETE FROM webhook_deliveries WHERE status <> 'pending' AND created_at < $1"
+        )
+        .bind(before)
+        .execute(pool)
+        .await
+        .map(|r| r.rows_affected()))
+        .context("prune webhook deliveries")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::Database;
+
+    #[tokio::test]
+    async fn deliveries_are_retried_until_they_fail() {
+        let db = Database::in_memory().await.unwrap();
+        let repo = WebhookDeliveryRepository::new(db.shared().clone());
+        let id = repo
+            .enqueue(NewWebhookDelivery {
+                webhook: "ci",
+                url: "https://ci.example.com/hook",
+                event: "session.failed",
+                payload: r#"{"event":"session.failed"}"#,
+            })
+            .await
+            .unwrap();
+
+        let due = repo.due(&db::now(), 10).await.unwrap();
+        assert_eq!(due.len(), 1);
+        assert_eq!(due[0].payload["event"], "session.failed");
+
+        repo.mark_attempt_failed(&id, Some(503), "HTTP 503", Some("9999-01-01 00:00:00"))
+            .await
+            .unwrap();
+        assert!(repo.due(&db::now(), 10).await.unwrap().is_empty());
+        let pending = repo.get(&id).await.unwrap().unwrap();
+        assert_eq!(pending.status, DeliveryStatus::Pending);
+        assert_eq!(pending.attempts, 1);
+
+        repo.mark_attempt_failed(&id, None, "connection refused", None)
+            .await
+            .unwrap();
+        let failed = DeliveryListQuery {
+            status: Some(DeliveryStatus::Failed),
+            ..Default::default()
+        };
+        assert_eq!(repo.list(&failed).await.unwrap().len(), 1);
+
+        assert!(repo.retry(&id).await.unwrap());
+        assert!(!repo.retry(&id).await.unwrap());
+        repo.mark_delivered(&id, 200).await.unwrap();
+        let delivered = repo.get(&id).await.unwrap().unwrap();
+        assert_eq!(delivered.status, DeliveryStatus::Delivered);
+        assert_eq!(delivered.attempts, 1);
+        assert!(delivered.delivered_at.is_some());
+    }
+}
@@ -4,7 +4,9 @@
 //! preferences (model catalog mode, harness settings, etc.).
 
 use anyhow::Result;
+use oqto_runner::protocol::ShellEnvironment;
 use serde::Deserialize;
+use std::collections::BTreeMap;
 use std::path::{Path, PathBuf};
 use tracing::{debug, warn};
 
@@ -29,12 +31,31 @@ pub struct ModelsConfig {
     pub mode: ModelMode,
 }
 
+/// `[shell]` section of `.oqto/config.toml`: the toolchain terminals and
+/// agents in the workspace get (local mode).
+#[derive(Debug, Clone, Deserialize, Default)]
+pub struct ShellConfig {
+    /// Directories prepended to `PATH`, relative to the workspace unless
+    /// absolute.
+    #[serde(default)]
+    pub path: Vec<String>,
+    /// Environment variables.
+    #[serde(default)]
+    pub env: BTreeMap<String, String>,
+    /// Aliases defined in terminals.
+    #[serde(default)]
+    pub aliases: BTreeMap<String, String>,
+}
+
 /// Top-level `.oqto/config.toml` structure.
 #[derive(Debug, Clone, Deserialize, Default)]
 pub struct WorkspaceConfig {
     /// Model catalog configuration.
     #[serde(default)]
     pub models: ModelsConfig,
+    /// Shell environment.
+    #[serde(default)]
+    pub shell: ShellConfig,
 }
 
 impl WorkspaceConfig {
@@ -117,6 +138,62 @@ impl WorkspaceConfig {
         }
     }
 
+    /// Resolve the `[shell]` section for `workspace`, or `None` if it declares
+    /// nothing. Entries that would break the shell are skipped with a warning,
+    /// as is `PATH` in `env` (use `path`).
+    pub fn shell_environment(&self, workspace: &Path) -> Option<ShellEnvironment> {
+        let shell = &self.shell;
+        let path: Vec<PathBuf> = shell
+            .path
+            .iter()
+            .filter(|dir| {
+                let valid = !dir.trim().is_empty() && !dir.contains(':');
+                if !valid {
+                    warn!("Ignoring [shell] path {:?} in {}", dir, workspace.display());
+                }
+                valid
+            })
+            .map(|dir| workspace.join(dir))
+            .collect();
+        let env: BTreeMap<String, String> = shell
+            .env
+            .iter()
+            .filter(|(key, value)| {
+                let valid = is_shell_name(key) && key.as_str() != "PATH" && !value.contains('\0');
+                if !valid {
+                    warn!("Ignoring [shell.env] {} in {}", key, workspace.display());
+                }
+                valid
+            })
+            .map(|(key, value)| (key.clone(), value.clone()))
+            .collect();
+        let aliases: BTreeMap<String, String> = shell
+            .aliases
+            .iter()
+            .filter(|(name, command)| {
+                let valid = !name.is_empty()
+                    && name
+                        .chars()
+                        .all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '.'))
+                    && !command.contains('\0');
+                if !valid {
+                    warn!(
+                        "Ignoring [shell.aliases] {} in {}",
+                        name,
+                        workspace.display()
+                    );
+                }
+                valid
+            })
+            .map(|(name, command)| (name.clone(), command.clone()))
+            .collect();
+
+        if path.is_empty() && env.is_empty() && aliases.is_empty() {
+            return None;
+        }
+        Some(ShellEnvironment { path, env, aliases })
+    }
+
     /// Merge global and workspace models.json files.
     ///
     /// Workspace providers are upserted into the global catalog.
@@ -155,6 +232,14 @@ impl WorkspaceConfig {
     }
 }
 
+/// Whether `name` is a valid environment variable name.
+fn is_shell_name(name: &str) -> bool {
+    name.chars()
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -190,6 +275,54 @@ mode = "restrict"
         assert_eq!(config.models.mode, ModelMode::Restrict);
     }
 
+    #[test]
+    fn test_shell_environment() {
+        let tmp = TempDir::new().unwrap();
+        assert!(
+            WorkspaceConfig::default()
+                .shell_environment(tmp.path())
+                .is_none()
+        );
+
+        let oqto_dir = tmp.path().join(".oqto");
+        std::fs::create_dir_all(&oqto_dir).unwrap();
+        std::fs::write(
+            oqto_dir.join("config.toml"),
+            r#"
+[shell]
+path = ["node_modules/.bin", "/opt/go/bin", "a:b"]
+
+[shell.env]
+RUST_LOG = "debug"
+PATH = "/nope"
+"1BAD" = "x"
+
+[shell.aliases]
+t = "cargo test"
+"rm -rf" = "nope"
+"#,
+        )
+        .unwrap();
+        let shell = WorkspaceConfig::load(tmp.path())
+            .shell_environment(tmp.path())
+            .unwrap();
+        assert_eq!(
+            shell.path,
+            [
+                tmp.path().join("node_modules/.bin"),
+                PathBuf::from("/opt/go/bin")
+            ]
+        );
+        assert_eq!(
+            shell.env,
+            BTreeMap::from([("RUST_LOG".to_string(), "debug".to_string())])
+        );
+        assert_eq!(
+            shell.aliases,
+            BTreeMap::from([("t".to_string(), "cargo test".to_string())])
+        );
+    }
+
     #[test]
     fn test_effective_mode_fallback() {
         let tmp = TempDir::new().unwrap();
@@ -197,6 +330,7 @@ mode = "restrict"
             models: ModelsConfig {
                 mode: ModelMode::Restrict,
             },
+            ..Default::default()
         };
         // No .oqto/models.json → falls back to global
         assert_eq!(config.effective_model_mode(tmp.path()), ModelMode::Global);
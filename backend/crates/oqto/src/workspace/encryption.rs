@@ -0,0 +1,26 @@
+//! At-rest encryption for individual workspaces.
+//!
+//! Encryption itself is performed by the user's runner (gocryptfs mounted as
+//! the workspace owner); the server only gates the feature and validates
+//! passphrases before forwarding them.
+
+use serde::{Deserialize, Serialize};
+
+/// `[workspace_encryption]` configuration section.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WorkspaceEncryptionConfig {
+    /// Allow users to encrypt workspaces (requires gocryptfs and FUSE on the host).
+    pub enabled: bool,
+    /// Minimum passphrase length in characters.
+    pub min_passphrase_length: usize,
+}
+
+impl Default for WorkspaceEncryptionConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            min_passphrase_length: 12,
+        }
+    }
+}
@@ -9,6 +9,9 @@ pub struct WorkspaceMeta {
     pub language: Option<String>,
     pub pinned: Option<bool>,
     pub bootstrap_pending: Option<bool>,
+    /// Image shown on the project card, relative to the workspace root.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub thumbnail: Option<String>,
 }
 
 pub fn workspace_meta_path(workspace_root: &Path) -> PathBuf {
@@ -1,4 +1,5 @@
 pub mod config;
+pub mod encryption;
 pub mod meta;
 
 pub use meta::workspace_display_name;
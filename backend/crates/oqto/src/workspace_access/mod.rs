@@ -0,0 +1,312 @@
+//! Delegated workspace access between users.
+//!
+//! A user can ask another user for time-boxed read-only or read-write access
+//! to one directory in the other user's workspace (e.g. to have an agent
+//! review a teammate's branch). Nothing is granted until the owner approves
+//! the request. Approved grants are enforced with POSIX ACLs that
+//! `oqto-usermgr` applies for the grantee's Linux account, so the grantee's
+//! runner and every session it hosts see the directory, and nothing else in
+//! the owner's home. A background sweep strips the ACL again when the grant
+//! expires.
+
+mod models;
+mod repository;
+
+pub use models::{
+    AccessMode, ApproveAccessRequest, CreateAccessRequest, GrantDirection, GrantListQuery,
+    GrantStatus, WorkspaceAccessConfig, WorkspaceAccessGrant,
+};
+pub use repository::{NewGrant, WorkspaceAccessRepository};
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+use tracing::{info, warn};
+
+use crate::local::LinuxUsersConfig;
+use crate::ws::{WsEvent, WsHub};
+
+/// Delegated workspace access service: requests, approvals and expiry.
+pub struct WorkspaceAccessService {
+    repo: WorkspaceAccessRepository,
+    config: WorkspaceAccessConfig,
+    linux_users: LinuxUsersConfig,
+    hub: Arc<WsHub>,
+}
+
+impl WorkspaceAccessService {
+    pub fn new(
+        repo: WorkspaceAccessRepository,
+        config: WorkspaceAccessConfig,
+        linux_users: LinuxUsersConfig,
+        hub: Arc<WsHub>,
+    ) -> Self {
+        Self {
+            repo,
+            config,
+            linux_users,
+            hub,
+        }
+    }
+
+    pub fn config(&self) -> &WorkspaceAccessConfig {
+        &self.config
+    }
+
+    pub fn repository(&self) -> &WorkspaceAccessRepository {
+        &self.repo
+    }
+
+    /// Record a pending request and send the owner the approval event.
+    pub async fn request(
+        &self,
+        grant: &NewGrant<'_>,
+        grantee_name: &str,
+    ) -> Result<WorkspaceAccessGrant> {
+        let grant = self.repo.create(grant).await?;
+        info!(
+            grant_id = %grant.id,
+            owner_id = %grant.owner_id,
+            grantee_id = %grant.grantee_id,
+            path = %grant.path,
+            mode = ?grant.mode,
+            "Workspace access requested"
+        );
+        self.notify(
+            &grant.owner_id,
+            "info",
+            "Workspace access requested",
+            format!(
+                "{grantee_name} asks for {} access to {} for {} minutes.",
+                mode_label(grant.mode),
+                grant.path,
+                grant.duration_minutes
+            ),
+            "workspace_access.request",
+            &grant,
+        )
+        .await;
+        Ok(grant)
+    }
+
+    /// Apply the ACL and start the grant's clock. The caller has checked that
+    /// the grant is pending and belongs to the approving owner.
+    pub async fn approve(
+        &self,
+        grant: &WorkspaceAccessGrant,
+        mode: AccessMode,
+        duration_minutes: i64,
+    ) -> Result<WorkspaceAccessGrant> {
+        self.apply_acl(grant, mode).await?;
+        if !self
+            .repo
+            .activate(&grant.id, mode, duration_minutes)
+            .await?
+        {
+            // Denied, revoked or expired while the ACL was being applied.
+            self.remove_acl(grant).await?;
+            anyhow::bail!("grant {} is no longer pending", grant.id);
+        }
+        let grant = self
+            .repo
+            .get(&grant.id)
+            .await?
+            .context("grant disappeared after approval")?;
+        info!(grant_id = %grant.id, expires_at = ?grant.expires_at, "Workspace access approved");
+        self.notify(
+            &grant.grantee_id,
+            "success",
+            "Workspace access approved",
+            format!(
+                "You have {} access to {} until {} UTC.",
+                mode_label(grant.mode),
+                grant.path,
+                grant.expires_at.as_deref().unwrap_or("-")
+            ),
+            "workspace_access.approved",
+            &grant,
+        )
+        .await;
+        Ok(grant)
+    }
+
+    /// Decline a pending request.
+    pub async fn deny(&self, grant: &WorkspaceAccessGrant) -> Result<bool> {
+        if !self
+            .repo
+            .close(&grant.id, GrantStatus::Denied, Some(&grant.owner_id))
+            .await?
+        {
+            return Ok(false);
+        }
+        info!(grant_id = %grant.id, "Workspace access denied");
+        self.notify(
+            &grant.grantee_id,
+            "info",
+            "Workspace access denied",
+            format!("Your request for {} was declined.", grant.path),
+            "workspace_access.denied",
+            grant,
+        )
+        .await;
+        Ok(true)
+    }
+
+    /// Withdraw a pending or active grant (owner or grantee).
+    pub async fn revoke(&self, grant: &WorkspaceAccessGrant, by_user_id: &str) -> Result<bool> {
+        if grant.status == GrantStatus::Active {
+            self.remove_acl(grant).await?;
+        }
+        if !self
+            .repo
+            .close(&grant.id, GrantStatus::Revoked, Some(by_user_id))
+            .await?
+        {
+            return Ok(false);
+        }
+        info!(grant_id = %grant.id, by = %by_user_id, "Workspace access revoked");
+        let other = if by_user_id == grant.owner_id {
+            &grant.grantee_id
+        } else {
+            &grant.owner_id
+        };
+        self.notify(
+            other,
+            "info",
+            "Workspace access revoked",
+            format!("Access to {} was revoked.", grant.path),
+            "workspace_access.revoked",
+            grant,
+        )
+        .await;
+        Ok(true)
+    }
+
+    /// Expire active grants past their deadline and unanswered requests.
+    pub async fn expire_due(&self) -> Result<usize> {
+        let mut expired = 0;
+        for grant in self.repo.list_expired_active().await? {
+            // Keep the grant active (and retry next sweep) if the ACL could
+            // not be removed, so it is never reported closed while enforced.
+            if let Err(e) = self.remove_acl(&grant).await {
+                warn!(grant_id = %grant.id, "Failed to remove expired workspace ACL: {:#}", e);
+                continue;
+            }
+            if self
+                .repo
+                .close(&grant.id, GrantStatus::Expired, None)
+                .await?
+            {
+                expired += 1;
+                self.notify(
+                    &grant.grantee_id,
+                    "info",
+                    "Workspace access expired",
+                    format!("Your access to {} has ended.", grant.path),
+                    "workspace_access.expired",
+                    &grant,
+                )
+                .await;
+            }
+        }
+        for grant in self
+            .repo
+            .list_stale_pending(self.config.request_ttl_minutes)
+            .await?
+        {
+            if self
+                .repo
+                .close(&grant.id, GrantStatus::Expired, None)
+                .await?
+            {
+                expired += 1;
+            }
+        }
+        Ok(expired)
+    }
+
+    /// Run `expire_due` on the configured interval.
+    pub fn start_sweep_task(self: &Arc<Self>) {
+        let service = Arc::clone(self);
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(Duration::from_secs(
+                service.config.sweep_interval_secs.max(10),
+            ));
+            interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+            loop {
+                interval.tick().await;
+                match service.expire_due().await {
+                    Ok(0) => {}
+                    Ok(n) => info!(expired = n, "Expired workspace access grants"),
+                    Err(e) => warn!("Workspace access sweep failed: {:#}", e),
+                }
+            }
+        });
+    }
+
+    async fn apply_acl(&self, grant: &WorkspaceAccessGrant, mode: AccessMode) -> Result<()> {
+        let args = serde_json::json!({
+            "owner": self.linux_users.linux_username(&grant.owner_id),
+            "grantee": self.linux_users.linux_username(&grant.grantee_id),
+            "path": grant.path,
+            "mode": mode.usermgr_mode(),
+        });
+        tokio::task::spawn_blocking(move || {
+            crate::local::linux_users::usermgr_request("grant-path-access", args)
+        })
+        .await
+        .context("grant-path-access task panicked")?
+        .context("applying workspace access ACL")
+    }
+
+    async fn remove_acl(&self, grant: &WorkspaceAccessGrant) -> Result<()> {
+        let remove_traverse = self
+            .repo
+            .count_other_active(&grant.owner_id, &grant.grantee_id, &grant.id)
+            .await?
+            == 0;
+        let args = serde_json::json!({
+            "owner": self.linux_users.linux_username(&grant.owner_id),
+            "grantee": self.linux_users.linux_username(&grant.grantee_id),
+            "path": grant.path,
+            "remove_traverse": remove_traverse,
+        });
+        tokio::task::spawn_blocking(move || {
+            crate::local::linux_users::usermgr_request("revoke-path-access", args)
+        })
+        .await
+        .context("revoke-path-access task panicked")?
+        .context("removing workspace access ACL")
+    }
+
+    async fn notify(
+        &self,
+        user_id: &str,
+        level: &str,
+        title: &str,
+        message: String,
+        category: &str,
+        grant: &WorkspaceAccessGrant,
+    ) {
+        self.hub
+            .send_to_user(
+                user_id,
+                WsEvent::Notification {
+                    level: level.to_string(),
+                    title: title.to_string(),
+                    message,
+                    category: category.to_string(),
+                    detail: serde_json::to_value(grant).ok(),
+                },
+            )
+            .await;
+    }
+}
+
+fn mode_label(mode: AccessMode) -> &'static str {
+    match mode {
+        AccessMode::ReadOnly => "read-only",
+        AccessMode::ReadWrite => "read-write",
+    }
+}
@@ -0,0 +1,139 @@
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+
+/// Delegated workspace access configuration.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WorkspaceAccessConfig {
+    /// Allow users to request access to directories in other users'
+    /// workspaces. Requires multi-user mode.
+    pub enabled: bool,
+    /// Grant duration when the request does not specify one.
+    pub default_duration_minutes: i64,
+    /// Longest grant a user can request.
+    pub max_duration_minutes: i64,
+    /// Pending requests not answered within this window expire.
+    pub request_ttl_minutes: i64,
+    /// Seconds between expiry sweeps.
+    pub sweep_interval_secs: u64,
+}
+
+impl Default for WorkspaceAccessConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            default_duration_minutes: 60,
+            max_duration_minutes: 8 * 60,
+            request_ttl_minutes: 24 * 60,
+            sweep_interval_secs: 60,
+        }
+    }
+}
+
+/// What the grantee may do inside the delegated directory.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, sqlx::Type)]
+#[serde(rename_all = "snake_case")]
+#[sqlx(rename_all = "snake_case")]
+pub enum AccessMode {
+    #[default]
+    ReadOnly,
+    ReadWrite,
+}
+
+impl AccessMode {
+    /// Mode name understood by `oqto-usermgr grant-path-access`.
+    pub fn usermgr_mode(self) -> &'static str {
+        match self {
+            Self::ReadOnly => "ro",
+            Self::ReadWrite => "rw",
+        }
+    }
+}
+
+/// Lifecycle of a grant.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, sqlx::Type)]
+#[serde(rename_all = "snake_case")]
+#[sqlx(rename_all = "snake_case")]
+pub enum GrantStatus {
+    /// Waiting for the owner's approval.
+    Pending,
+    /// Approved and enforced until `expires_at`.
+    Active,
+    /// Declined by the owner.
+    Denied,
+    /// Withdrawn by either party before expiry.
+    Revoked,
+    /// Ran past `expires_at` (or the request was never answered).
+    Expired,
+}
+
+/// Time-boxed access from one user to a directory in another user's workspace.
+#[derive(Debug, Clone, Serialize, FromRow)]
+pub struct WorkspaceAccessGrant {
+    pub id: String,
+    /// User whose workspace contains `path`.
+    pub owner_id: String,
+    /// User who is given access (their runner and sessions).
+    pub grantee_id: String,
+    /// Session the access was requested for, if any (informational).
+    pub session_id: Option<String>,
+    /// Absolute directory in the owner's workspace.
+    pub path: String,
+    pub mode: AccessMode,
+    pub status: GrantStatus,
+    pub reason: Option<String>,
+    pub duration_minutes: i64,
+    pub requested_at: String,
+    pub decided_at: Option<String>,
+    pub expires_at: Option<String>,
+    pub revoked_at: Option<String>,
+    pub revoked_by: Option<String>,
+}
+
+/// Request body for asking another user for access.
+#[derive(Debug, Clone, Deserialize)]
+pub struct CreateAccessRequest {
+    /// Owner's user id or username.
+    pub owner: String,
+    /// Directory relative to the owner's workspace root.
+    pub path: String,
+    #[serde(default)]
+    pub mode: AccessMode,
+    #[serde(default)]
+    pub duration_minutes: Option<i64>,
+    #[serde(default)]
+    pub session_id: Option<String>,
+    #[serde(default)]
+    pub reason: Option<String>,
+}
+
+/// Optional body when approving; lets the owner shorten the grant or
+/// downgrade it to read-only.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct ApproveAccessRequest {
+    #[serde(default)]
+    pub duration_minutes: Option<i64>,
+    #[serde(default)]
+    pub mode: Option<AccessMode>,
+}
+
+/// Which side of the grants to list.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum GrantDirection {
+    /// Grants on the caller's workspace.
+    Incoming,
+    /// Grants the caller requested.
+    #[default]
+    Outgoing,
+}
+
+/// Query for listing grants.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct GrantListQuery {
+    #[serde(default)]
+    pub direction: GrantDirection,
+    /// Include denied, revoked and expired grants.
+    #[serde(default)]
+    pub include_inactive: bool,
+}
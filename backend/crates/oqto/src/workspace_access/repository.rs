@@ -0,0 +1,350 @@
+use anyhow::{Context, Result};
+use sqlx::{QueryBuilder, Sqlite, SqlitePool};
+
+use super::{AccessMode, GrantDirection, GrantListQuery, GrantStatus, WorkspaceAccessGrant};
+
+const GRANT_COLUMNS: &str = "id, owner_id, grantee_id, session_id, path, mode, status, reason, \
+     duration_minutes, requested_at, decided_at, expires_at, revoked_at, revoked_by";
+
+/// Fields for a new pending request.
+#[derive(Debug, Clone)]
+pub struct NewGrant<'a> {
+    pub owner_id: &'a str,
+    pub grantee_id: &'a str,
+    pub session_id: Option<&'a str>,
+    pub path: &'a str,
+    pub mode: AccessMode,
+    pub reason: Option<&'a str>,
+    pub duration_minutes: i64,
+}
+
+#[derive(Debug, Clone)]
+pub struct WorkspaceAccessRepository {
+    pool: SqlitePool,
+}
+
+impl WorkspaceAccessRepository {
+    pub fn new(pool: SqlitePool) -> Self {
+        Self { pool }
+    }
+
+    fn generate_id() -> String {
+        format!("wag_{}", nanoid::nanoid!(12))
+    }
+
+    pub async fn create(&self, grant: &NewGrant<'_>) -> Result<WorkspaceAccessGrant> {
+        let id = Self::generate_id();
+        sqlx::query(
+            r#"INSERT INTO workspace_access_grants
+                   (id, owner_id, grantee_id, session_id, path, mode, reason, duration_minutes)
+               VALUES (?, ?, ?, ?, ?, ?, ?, ?)"#,
+        )
+        .bind(&id)
+        .bind(grant.owner_id)
+        .bind(grant.grantee_id)
+        .bind(grant.session_id)
+        .bind(grant.path)
+        .bind(grant.mode)
+        .bind(grant.reason)
+        .bind(grant.duration_minutes)
+        .execute(&self.pool)
+        .await
+        .context("insert workspace access grant")?;
+
+        self.get(&id)
+            .await?
+            .ok_or_else(|| anyhow::anyhow!("Grant not found after creation"))
+    }
+
+    pub async fn get(&self, id: &str) -> Result<Option<WorkspaceAccessGrant>> {
+        sqlx::query_as::<_, WorkspaceAccessGrant>(&format!(
+            "SELECT {GRANT_COLUMNS} FROM workspace_access_grants WHERE id = ?"
+        ))
+        .bind(id)
+        .fetch_optional(&self.pool)
+        .await
+        .context("get workspace access grant")
+    }
+
+    /// Grants where `user_id` is the owner (incoming) or grantee (outgoing),
+    /// newest first.
+    pub async fn list_for_user(
+        &self,
+        user_id: &str,
+        query: &GrantListQuery,
+    ) -> Result<Vec<WorkspaceAccessGrant>> {
+        let mut qb = QueryBuilder::<Sqlite>::new(format!(
+            "SELECT {GRANT_COLUMNS} FROM workspace_access_grants WHERE "
+        ));
+        qb.push(match query.direction {
+            GrantDirection::Incoming => "owner_id = ",
+            GrantDirection::Outgoing => "grantee_id = ",
+        })
+        .push_bind(user_id.to_string());
+        if !query.include_inactive {
+            qb.push(" AND status IN ('pending', 'active')");
+        }
+        qb.push(" ORDER BY requested_at DESC, id DESC");
+
+        qb.build_query_as::<WorkspaceAccessGrant>()
+            .fetch_all(&self.pool)
+            .await
+            .context("list workspace access grants")
+    }
+
+    /// Pending or active grant for the same owner, grantee and path.
+    pub async fn find_open(
+        &self,
+        owner_id: &str,
+        grantee_id: &str,
+        path: &str,
+    ) -> Result<Option<WorkspaceAccessGrant>> {
+        sqlx::query_as::<_, WorkspaceAccessGrant>(&format!(
+            "SELECT {GRANT_COLUMNS} FROM workspace_access_grants
+             WHERE owner_id = ? AND grantee_id = ? AND path = ?
+               AND status IN ('pending', 'active')"
+        ))
+        .bind(owner_id)
+        .bind(grantee_id)
+        .bind(path)
+        .fetch_optional(&self.pool)
+        .await
+        .context("find open workspace access grant")
+    }
+
+    /// Number of active grants from `owner_id` to `grantee_id`, excluding
+    /// `except_id`. Used to decide whether traverse ACLs can be removed.
+    pub async fn count_other_active(
+        &self,
+        owner_id: &str,
+        grantee_id: &str,
+        except_id: &str,
+    ) -> Result<i64> {
+        let (count,) = sqlx::query_as::<_, (i64,)>(
+            r#"SELECT COUNT(*) FROM workspace_access_grants
+               WHERE owner_id = ? AND grantee_id = ? AND id != ? AND status = 'active'"#,
+        )
+        .bind(owner_id)
+        .bind(grantee_id)
+        .bind(except_id)
+        .fetch_one(&self.pool)
+        .await
+        .context("count active workspace access grants")?;
+        Ok(count)
+    }
+
+    /// Move a pending grant to active. Returns false if it was no longer
+    /// pending.
+    pub async fn activate(
+        &self,
+        id: &str,
+        mode: AccessMode,
+        duration_minutes: i64,
+    ) -> Result<bool> {
+        let result = sqlx::query(
+            r#"UPDATE workspace_access_grants
+               SET status = 'active', mode = ?, duration_minutes = ?,
+                   decided_at = datetime('now'),
+                   expires_at = datetime('now', ?)
+               WHERE id = ? AND status = 'pending'"#,
+        )
+        .bind(mode)
+        .bind(duration_minutes)
+        .bind(format!("+{duration_minutes} minutes"))
+        .bind(id)
+        .execute(&self.pool)
+        .await
+        .context("activate workspace access grant")?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Close a pending or active grant with a terminal status. Returns false
+    /// if it was already closed.
+    pub async fn close(&self, id: &str, status: GrantStatus, by: Option<&str>) -> Result<bool> {
+        let result = sqlx::query(
+            r#"UPDATE workspace_access_grants
+               SET status = ?,
+                   decided_at = CASE WHEN status = 'pending' THEN datetime('now') ELSE decided_at END,
+                   revoked_at = CASE WHEN ? IN ('revoked', 'expired') THEN datetime('now') ELSE revoked_at END,
+                   revoked_by = COALESCE(?, revoked_by)
+               WHERE id = ? AND status IN ('pending', 'active')"#,
+        )
+        .bind(status)
+        .bind(status)
+        .bind(by)
+        .bind(id)
+        .execute(&self.pool)
+        .await
+        .context("close workspace access grant")?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Active grants past their expiry.
+    pub async fn list_expired_active(&self) -> Result<Vec<WorkspaceAccessGrant>> {
+        sqlx::query_as::<_, WorkspaceAccessGrant>(&format!(
+            "SELECT {GRANT_COLUMNS} FROM workspace_access_grants
+             WHERE status = 'active' AND expires_at <= datetime('now')"
+        ))
+        .fetch_all(&self.pool)
+        .await
+        .context("list expired workspace access grants")
+    }
+
+    /// Pending requests older than `ttl_minutes`.
+    pub async fn list_stale_pending(&self, ttl_minutes: i64) -> Result<Vec<WorkspaceAccessGrant>> {
+        sqlx::query_as::<_, WorkspaceAccessGrant>(&format!(
+            "SELECT {GRANT_COLUMNS} FROM workspace_access_grants
+             WHERE status = 'pending' AND requested_at <= datetime('now', ?)"
+        ))
+        .bind(format!("-{ttl_minutes} minutes"))
+        .fetch_all(&self.pool)
+        .await
+        .context("list stale workspace access requests")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::Database;
+
+    async fn setup() -> WorkspaceAccessRepository {
+        let db = Database::in_memory().await.unwrap();
+        for id in ["alice", "bob"] {
+            sqlx::query(
+                "INSERT INTO users (id, username, email, display_name) VALUES (?, ?, ?, ?)",
+            )
+            .bind(id)
+            .bind(id)
+            .bind(format!("{id}@example.com"))
+            .bind(id)
+            .execute(db.pool())
+            .await
+            .unwrap();
+        }
+        WorkspaceAccessRepository::new(db.pool().clone())
+    }
+
+    fn new_grant(path: &str) -> NewGrant<'_> {
+        NewGrant {
+            owner_id: "bob",
+            grantee_id: "alice",
+            session_id: Some("ses_1"),
+            path,
+            mode: AccessMode::ReadOnly,
+            reason: Some("review feature branch"),
+            duration_minutes: 30,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_grant_lifecycle() {
+        let repo = setup().await;
+        let grant = repo
+            .create(&new_grant("/home/oqto_bob/oqto/app"))
+            .await
+            .unwrap();
+        assert_eq!(grant.status, GrantStatus::Pending);
+        assert!(grant.expires_at.is_none());
+
+        let incoming = repo
+            .list_for_user(
+                "bob",
+                &GrantListQuery {
+                    direction: GrantDirection::Incoming,
+                    include_inactive: false,
+                },
+            )
+            .await
+            .unwrap();
+        assert_eq!(incoming.len(), 1);
+        assert!(
+            repo.list_for_user("bob", &GrantListQuery::default())
+                .await
+                .unwrap()
+                .is_empty()
+        );
+
+        assert!(
+            repo.activate(&grant.id, AccessMode::ReadWrite, 15)
+                .await
+                .unwrap()
+        );
+        assert!(
+            !repo
+                .activate(&grant.id, AccessMode::ReadWrite, 15)
+                .await
+                .unwrap()
+        );
+        let active = repo.get(&grant.id).await.unwrap().unwrap();
+        assert_eq!(active.status, GrantStatus::Active);
+        assert_eq!(active.mode, AccessMode::ReadWrite);
+        assert!(active.expires_at.is_some());
+        assert!(repo.list_expired_active().await.unwrap().is_empty());
+
+        assert!(
+            repo.close(&grant.id, GrantStatus::Revoked, Some("bob"))
+                .await
+                .unwrap()
+        );
+        assert!(
+            !repo
+                .close(&grant.id, GrantStatus::Expired, None)
+                .await
+                .unwrap()
+        );
+        let revoked = repo.get(&grant.id).await.unwrap().unwrap();
+        assert_eq!(revoked.status, GrantStatus::Revoked);
+        assert_eq!(revoked.revoked_by.as_deref(), Some("bob"));
+        assert!(revoked.revoked_at.is_some());
+    }
+
+    #[tokio::test]
+    async fn test_find_open_and_active_counts() {
+        let repo = setup().await;
+        let first = repo
+            .create(&new_grant("/home/oqto_bob/oqto/app"))
+            .await
+            .unwrap();
+        let second = repo
+            .create(&new_grant("/home/oqto_bob/oqto/docs"))
+            .await
+            .unwrap();
+        repo.activate(&first.id, AccessMode::ReadOnly, 30)
+            .await
+            .unwrap();
+        repo.activate(&second.id, AccessMode::ReadOnly, 30)
+            .await
+            .unwrap();
+
+        assert!(
+            repo.find_open("bob", "alice", "/home/oqto_bob/oqto/app")
+                .await
+                .unwrap()
+                .is_some()
+        );
+        assert!(
+            repo.find_open("bob", "alice", "/home/oqto_bob/oqto/other")
+                .await
+                .unwrap()
+                .is_none()
+        );
+        assert_eq!(
+            repo.count_other_active("bob", "alice", &first.id)
+                .await
+                .unwrap(),
+            1
+        );
+
+        sqlx::query(
+            "UPDATE workspace_access_grants SET expires_at = datetime('now', '-1 minutes') WHERE id = ?",
+        )
+        .bind(&second.id)
+        .execute(&repo.pool)
+        .await
+        .unwrap();
+        let expired = repo.list_expired_active().await.unwrap();
+        assert_eq!(expired.len(), 1);
+        assert_eq!(expired[0].id, second.id);
+    }
+}
@@ -0,0 +1,154 @@
+//! Content-defined chunking.
+//!
+//! Files are cut where a rolling gear hash over the last 64 bytes hits a
+//! bit pattern, so an insertion only changes the chunks around it and the
+//! rest of the file still deduplicates against earlier snapshots. Chunks
+//! are 256 KiB to 4 MiB, about 1 MiB on average.
+
+use std::io::{self, Read};
+
+use sha2::{Digest, Sha256};
+
+/// No cut before this many bytes.
+pub const MIN_CHUNK: usize = 256 * 1024;
+/// Always cut after this many bytes.
+pub const MAX_CHUNK: usize = 4 * 1024 * 1024;
+/// 20 high bits of the hash must be zero: one cut per MiB on average.
+const MASK: u64 = ((1 << 20) - 1) << 44;
+
+/// Random values per byte, from splitmix64.
+const GEAR: [u64; 256] = {
+    let mut table = [0u64; 256];
+    let mut state = 0u64;
+    let mut i = 0;
+    while i < 256 {
+        state = state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        table[i] = z ^ (z >> 31);
+        i += 1;
+    }
+    table
+};
+
+/// Length of the first chunk of `data`, which holds either `MAX_CHUNK`
+/// bytes or the rest of the file.
+fn chunk_len(data: &[u8]) -> usize {
+    if data.len() <= MIN_CHUNK {
+        return data.len();
+    }
+    let end = data.len().min(MAX_CHUNK);
+    let mut hash = 0u64;
+    for (i, byte) in data[..end].iter().enumerate().skip(MIN_CHUNK) {
+        hash = (hash << 1).wrapping_add(GEAR[*byte as usize]);
+        if hash & MASK == 0 {
+            return i + 1;
+        }
+    }
+    end
+}
+
+/// Hex SHA-256 of a chunk, its address in the chunk store.
+pub fn chunk_hash(data: &[u8]) -> String {
+    hex::encode(Sha256::digest(data))
+}
+
+/// Splits a reader into chunks.
+pub struct Chunker<R> {
+    reader: R,
+    buf: Vec<u8>,
+    eof: bool,
+}
+
+impl<R: Read> Chunker<R> {
+    pub fn new(reader: R) -> Self {
+        Self {
+            reader,
+            buf: Vec::with_capacity(MAX_CHUNK),
+            eof: false,
+        }
+    }
+
+    /// The next chunk; None at the end of the input.
+    pub fn next_chunk(&mut self) -> io::Result<Option<Vec<u8>>> {
+        while !self.eof && self.buf.len() < MAX_CHUNK {
+            let start = self.buf.len();
+            self.buf.resize(MAX_CHUNK, 0);
+            match self.reader.read(&mut self.buf[start..]) {
+                Ok(0) => {
+                    self.buf.truncate(start);
+                    self.eof = true;
+                }
+                Ok(n) => self.buf.truncate(start + n),
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => self.buf.truncate(start),
+                Err(e) => {
+                    self.buf.truncate(start);
+                    return Err(e);
+                }
+            }
+        }
+        if self.buf.is_empty() {
+            return Ok(None);
+        }
+        let len = chunk_len(&self.buf);
+        let rest = self.buf.split_off(len);
+        Ok(Some(std::mem::replace(&mut self.buf, rest)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn noise(len: usize, seed: u64) -> Vec<u8> {
+        let mut state = seed;
+        (0..len)
+            .map(|_| {
+                state ^= state << 13;
+                state ^= state >> 7;
+                state ^= state << 17;
+                state as u8
+            })
+            .collect()
+    }
+
+    fn chunks(data: &[u8]) -> Vec<Vec<u8>> {
+        let mut chunker = Chunker::new(data);
+        let mut chunks = Vec::new();
+        while let Some(chunk) = chunker.next_chunk().unwrap() {
+            chunks.push(chunk);
+        }
+        chunks
+    }
+
+    #[test]
+    fn test_chunks_cover_input_within_bounds() {
+        let data = noise(12 * 1024 * 1024, 7);
+        let chunks = chunks(&data);
+        assert!(chunks.len() > 3);
+        assert_eq!(chunks.concat(), data);
+        for chunk in &chunks[..chunks.len() - 1] {
+            assert!((MIN_CHUNK..=MAX_CHUNK).contains(&chunk.len()));
+        }
+
+        assert!(self::chunks(&[]).is_empty());
+        assert_eq!(self::chunks(b"small file"), vec![b"small file".to_vec()]);
+    }
+
+    #[test]
+    fn test_insertion_keeps_later_chunks() {
+        let data = noise(12 * 1024 * 1024, 11);
+        let mut edited = b"a few new bytes at the start".to_vec();
+        edited.extend_from_slice(&data);
+
+        let before: Vec<String> = chunks(&data).iter().map(|c| chunk_hash(c)).collect();
+        let after: Vec<String> = chunks(&edited).iter().map(|c| chunk_hash(c)).collect();
+        let shared = after.iter().filter(|hash| before.contains(hash)).count();
+        assert!(
+            shared + 2 >= before.len(),
+            "only {shared} of {} chunks survived the insertion",
+            before.len()
+        );
+    }
+}
@@ -0,0 +1,789 @@
+//! Incremental, deduplicated workspace backups.
+//!
+//! A snapshot is a [`Tree`] of a workspace's paths whose files point at
+//! content-defined chunks (see [`chunker`]). Chunks and trees are stored in
+//! the object storage backend under their SHA-256, so a chunk is stored once
+//! no matter how many files, snapshots, workspaces or users contain it: a
+//! hundred copies of the same `node_modules` cost one. Files whose size and
+//! mtime match the previous snapshot are not read again.
+//!
+//! Layout under `workspace-backups/`:
+//!
+//! - `chunks/<ab>/<sha256>`: file contents
+//! - `trees/<sha256>.json`: a [`Tree`]
+//! - `snapshots/<user_id>/<workspace>/<snapshot_id>.json`: a [`Snapshot`]
+//!
+//! Pruning applies the [`RetentionPolicy`] to every workspace's snapshots
+//! and then deletes the trees and chunks no snapshot refers to any more.
+
+pub mod chunker;
+mod models;
+
+pub use models::{
+    BackupResult, CreateBackupRequest, EntryKind, PruneReport, RestoreRequest, RestoreResult,
+    RetentionPolicy, Snapshot, SnapshotDetail, SnapshotEntry, SnapshotQuery, Tree, TreeEntry,
+    WorkspaceBackupsConfig,
+};
+
+use std::collections::{HashMap, HashSet};
+use std::os::unix::fs::{MetadataExt, PermissionsExt};
+use std::path::{Component, Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use anyhow::{Context, Result, anyhow, bail};
+use chrono::{DateTime, Datelike, Duration, Utc};
+use oqto_files::archive::ExclusionRules;
+use tokio::io::AsyncWriteExt;
+use tokio::sync::{RwLock, mpsc};
+use tracing::{debug, info};
+
+use crate::storage::StorageBackend;
+use chunker::{Chunker, chunk_hash};
+
+const PREFIX: &str = "workspace-backups";
+
+fn chunk_key(hash: &str) -> String {
+    format!("{PREFIX}/chunks/{}/{hash}", &hash[..2])
+}
+
+fn tree_key(hash: &str) -> String {
+    format!("{PREFIX}/trees/{hash}.json")
+}
+
+fn snapshot_prefix(user_id: &str, workspace: &str) -> String {
+    format!("{PREFIX}/snapshots/{user_id}/{workspace}/")
+}
+
+fn snapshot_key(user_id: &str, workspace: &str, id: &str) -> String {
+    format!("{}{id}.json", snapshot_prefix(user_id, workspace))
+}
+
+/// Whether `id` looks like a snapshot ID (`20261015T120000Z-1a2b3c`).
+pub fn valid_snapshot_id(id: &str) -> bool {
+    !id.is_empty() && id.len() <= 64 && id.chars().all(|c| c.is_ascii_alphanumeric() || c == '-')
+}
+
+fn new_snapshot_id(created_at: DateTime<Utc>) -> String {
+    format!(
+        "{}-{}",
+        created_at.format("%Y%m%dT%H%M%SZ"),
+        hex::encode(rand::random::<[u8; 3]>())
+    )
+}
+
+/// Takes, lists, restores and prunes snapshots.
+pub struct WorkspaceBackupService {
+    config: WorkspaceBackupsConfig,
+    storage: Arc<dyn StorageBackend>,
+    /// Backups hold it shared and pruning exclusively, so no chunk is
+    /// deleted while a running backup counts on it being there.
+    prune_lock: RwLock<()>,
+    /// When the scheduler last scanned each `user_id/workspace`, so one that
+    /// did not change is not rescanned before the next interval.
+    checked: Mutex<HashMap<String, DateTime<Utc>>>,
+}
+
+impl WorkspaceBackupService {
+    pub fn new(config: WorkspaceBackupsConfig, storage: Arc<dyn StorageBackend>) -> Self {
+        Self {
+            config,
+            storage,
+            prune_lock: RwLock::new(()),
+            checked: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn config(&self) -> &WorkspaceBackupsConfig {
+        &self.config
+    }
+
+    /// Snapshots of a user's workspace, or of all their workspaces, newest
+    /// first.
+    pub async fn list(&self, user_id: &str, workspace: Option<&str>) -> Result<Vec<Snapshot>> {
+        let prefix = match workspace {
+            Some(workspace) => snapshot_prefix(user_id, workspace),
+            None => format!("{PREFIX}/snapshots/{user_id}/"),
+        };
+        let mut snapshots = Vec::new();
+        for key in self.storage.list(&prefix).await? {
+            if let Some(snapshot) = self.read_snapshot(&key).await? {
+                snapshots.push(snapshot);
+            }
+        }
+        snapshots.sort_by(|a, b| b.created_at.cmp(&a.created_at).then(b.id.cmp(&a.id)));
+        Ok(snapshots)
+    }
+
+    async fn read_snapshot(&self, key: &str) -> Result<Option<Snapshot>> {
+        match self.storage.get(key).await? {
+            Some(data) => Ok(Some(
+                serde_json::from_slice(&data).with_context(|| format!("parsing {key}"))?,
+            )),
+            None => Ok(None),
+        }
+    }
+
+    pub async fn get(&self, user_id: &str, workspace: &str, id: &str) -> Result<Option<Snapshot>> {
+        if !valid_snapshot_id(id) {
+            return Ok(None);
+        }
+        self.read_snapshot(&snapshot_key(user_id, workspace, id))
+            .await
+    }
+
+    /// The snapshot to restore: `id` if given, otherwise the newest taken at
+    /// or before `at` (or the newest of all).
+    pub async fn resolve(
+        &self,
+        user_id: &str,
+        workspace: &str,
+        id: Option<&str>,
+        at: Option<DateTime<Utc>>,
+    ) -> Result<Option<Snapshot>> {
+        if let Some(id) = id {
+            return self.get(user_id, workspace, id).await;
+        }
+        Ok(self
+            .list(user_id, Some(workspace))
+            .await?
+            .into_iter()
+            .find(|s| at.is_none_or(|at| s.created_at <= at)))
+    }
+
+    pub async fn tree(&self, snapshot: &Snapshot) -> Result<Tree> {
+        let data = self
+            .storage
+            .get(&tree_key(&snapshot.tree))
+            .await?
+            .ok_or_else(|| {
+                anyhow!(
+                    "tree {} of snapshot {} is missing",
+                    snapshot.tree,
+                    snapshot.id
+                )
+            })?;
+        serde_json::from_slice(&data).context("parsing snapshot tree")
+    }
+
+    /// A snapshot with its paths, for browsing before a restore.
+    pub async fn detail(&self, snapshot: Snapshot) -> Result<SnapshotDetail> {
+        let tree = self.tree(&snapshot).await?;
+        Ok(SnapshotDetail {
+            entries: tree
+                .entries
+                .into_iter()
+                .map(|entry| SnapshotEntry {
+                    modified_at: (entry.kind == EntryKind::File)
+                        .then(|| DateTime::from_timestamp(entry.mtime, 0))
+                        .flatten(),
+                    path: entry.path,
+                    kind: entry.kind,
+                    size: entry.size,
+                })
+                .collect(),
+            snapshot,
+        })
+    }
+
+    /// Forget a snapshot. Its chunks go with the next prune.
+    pub async fn delete(&self, snapshot: &Snapshot) -> Result<()> {
+        self.storage
+            .delete(&snapshot_key(
+                &snapshot.user_id,
+                &snapshot.workspace,
+                &snapshot.id,
+            ))
+            .await
+    }
+
+    /// Snapshot `dir` as `workspace` of `user_id`.
+    pub async fn backup(&self, user_id: &str, workspace: &str, dir: &Path) -> Result<BackupResult> {
+        let latest = self
+            .list(user_id, Some(workspace))
+            .await?
+            .into_iter()
+            .next();
+        self.backup_after(user_id, workspace, dir, latest).await
+    }
+
+    /// Snapshot `dir` if its newest snapshot is older than
+    /// `interval_hours`. None when not due.
+    pub async fn backup_if_due(
+        &self,
+        user_id: &str,
+        workspace: &str,
+        dir: &Path,
+        now: DateTime<Utc>,
+    ) -> Result<Option<BackupResult>> {
+        let key = format!("{user_id}/{workspace}");
+        let latest = self
+            .list(user_id, Some(workspace))
+            .await?
+            .into_iter()
+            .next();
+        let checked = self.checked.lock().unwrap().get(&key).copied();
+        let last = latest.as_ref().map(|s| s.created_at).max(checked);
+        let interval = Duration::hours(self.config.interval_hours.max(1) as i64);
+        if last.is_some_and(|last| last + interval > now) {
+            return Ok(None);
+        }
+        let result = self.backup_after(user_id, workspace, dir, latest).await?;
+        self.checked.lock().unwrap().insert(key, now);
+        Ok(Some(result))
+    }
+
+    async fn backup_after(
+        &self,
+        user_id: &str,
+        workspace: &str,
+        dir: &Path,
+        latest: Option<Snapshot>,
+    ) -> Result<BackupResult> {
+        let _shared = self.prune_lock.read().await;
+        let started = std::time::Instant::now();
+        let previous: HashMap<String, TreeEntry> = match &latest {
+            Some(latest) => self
+                .tree(latest)
+                .await?
+                .entries
+                .into_iter()
+                .filter(|entry| entry.kind == EntryKind::File)
+                .map(|entry| (entry.path.clone(), entry))
+                .collect(),
+            None => HashMap::new(),
+        };
+        // Chunks of the previous snapshot are stored; no need to ask.
+        let mut stored: HashSet<String> = previous
+            .values()
+            .flat_map(|entry| entry.chunks.iter().cloned())
+            .collect();
+
+        let (tx, mut rx) = mpsc::channel(16);
+        let scan_dir = dir.to_path_buf();
+        let exclude = self.config.exclude.clone();
+        let scanner =
+            tokio::task::spawn_blocking(move || scan(&scan_dir, &exclude, &previous, &tx));
+
+        let mut entries = Vec::new();
+        let (mut new_chunks, mut new_bytes) = (0, 0);
+        while let Some(item) = rx.recv().await {
+            match item {
+                Scanned::Chunk { hash, data } => {
+                    if !stored.insert(hash.clone()) {
+                        continue;
+                    }
+                    let key = chunk_key(&hash);
+                    if !self.storage.exists(&key).await? {
+                        new_chunks += 1;
+                        new_bytes += data.len() as u64;
+                        self.storage
+                            .put(&key, data, "application/octet-stream")
+                            .await?;
+                    }
+                }
+                Scanned::Entry(entry) => entries.push(entry),
+            }
+        }
+        scanner.await.context("workspace scan panicked")??;
+
+        entries.sort_by(|a, b| a.path.cmp(&b.path));
+        let tree = Tree { entries };
+        let tree_json = serde_json::to_vec(&tree)?;
+        let tree_hash = chunk_hash(&tree_json);
+        if let Some(latest) = latest.filter(|latest| latest.tree == tree_hash) {
+            debug!(user_id = %user_id, workspace = %workspace, "Workspace unchanged since last backup");
+            return Ok(BackupResult {
+                snapshot: latest,
+                unchanged: true,
+            });
+        }
+        let key = tree_key(&tree_hash);
+        if !self.storage.exists(&key).await? {
+            self.storage
+                .put(&key, tree_json, "application/json")
+                .await?;
+        }
+
+        let files = tree.entries.iter().filter(|e| e.kind == EntryKind::File);
+        let created_at = Utc::now();
+        let snapshot = Snapshot {
+            id: new_snapshot_id(created_at),
+            user_id: user_id.to_string(),
+            workspace: workspace.to_string(),
+            created_at,
+            tree: tree_hash,
+            files: files.clone().count() as u64,
+            bytes: files.map(|e| e.size).sum(),
+            new_chunks,
+            new_bytes,
+            parent: latest.map(|latest| latest.id),
+        };
+        self.storage
+            .put(
+                &snapshot_key(user_id, workspace, &snapshot.id),
+                serde_json::to_vec_pretty(&snapshot)?,
+                "application/json",
+            )
+            .await?;
+        info!(
+            user_id = %user_id,
+            workspace = %workspace,
+            snapshot_id = %snapshot.id,
+            files = snapshot.files,
+            bytes = snapshot.bytes,
+            new_bytes,
+            elapsed_ms = started.elapsed().as_millis() as u64,
+            "Workspace backed up"
+        );
+        Ok(BackupResult {
+            snapshot,
+            unchanged: false,
+        })
+    }
+
+    /// Write `snapshot` (or the given `paths` of it) into `dest`, replacing
+    /// files that are there. Returns the files and bytes written.
+    pub async fn restore(
+        &self,
+        snapshot: &Snapshot,
+        dest: &Path,
+        paths: &[String],
+    ) -> Result<(u64, u64)> {
+        let tree = self.tree(snapshot).await?;
+        let selected = |path: &str| {
+            paths.is_empty()
+                || paths.iter().any(|p| {
+                    let p = p.trim_matches('/');
+                    path == p || path.starts_with(&format!("{p}/"))
+                })
+        };
+        tokio::fs::create_dir_all(dest)
+            .await
+            .with_context(|| format!("creating {}", dest.display()))?;
+
+        let (mut files, mut bytes) = (0, 0);
+        let mut dirs = Vec::new();
+        for entry in tree.entries.iter().filter(|e| selected(&e.path)) {
+            let Some(relative) = safe_relative(&entry.path) else {
+                continue;
+            };
+            let out = dest.join(relative);
+            // Never write through a link that is in the way, and make room
+            // for a link.
+            if let Ok(metadata) = tokio::fs::symlink_metadata(&out).await
+                && (metadata.file_type().is_symlink()
+                    || (entry.kind == EntryKind::Symlink && !metadata.is_dir()))
+            {
+                tokio::fs::remove_file(&out).await?;
+            }
+            if let Some(parent) = out.parent() {
+                tokio::fs::create_dir_all(parent)
+                    .await
+                    .with_context(|| format!("creating {}", parent.display()))?;
+            }
+            match entry.kind {
+                EntryKind::Dir => {
+                    tokio::fs::create_dir_all(&out)
+                        .await
+                        .with_context(|| format!("creating {}", out.display()))?;
+                    dirs.push((out, entry.mode));
+                }
+                EntryKind::Symlink => {
+                    if let Some(target) = &entry.target {
+                        tokio::fs::symlink(target, &out)
+                            .await
+                            .with_context(|| format!("linking {}", out.display()))?;
+                    }
+                }
+                EntryKind::File => {
+                    let mut file = tokio::fs::File::create(&out)
+                        .await
+                        .with_context(|| format!("writing {}", out.display()))?;
+                    for hash in &entry.chunks {
+                        let data =
+                            self.storage.get(&chunk_key(hash)).await?.ok_or_else(|| {
+                                anyhow!("chunk {hash} of {} is missing", entry.path)
+                            })?;
+                        if chunk_hash(&data) != *hash {
+                            bail!("chunk {hash} of {} is corrupted", entry.path);
+                        }
+                        file.write_all(&data).await?;
+                        bytes += data.len() as u64;
+                    }
+                    file.flush().await?;
+                    tokio::fs::set_permissions(&out, std::fs::Permissions::from_mode(entry.mode))
+                        .await?;
+                    files += 1;
+                }
+            }
+        }
+        // Last, so read-only directories do not block their contents.
+        for (dir, mode) in dirs.into_iter().rev() {
+            tokio::fs::set_permissions(&dir, std::fs::Permissions::from_mode(mode)).await?;
+        }
+        Ok((files, bytes))
+    }
+
+    /// Apply the retention policy to every workspace's snapshots and delete
+    /// what no remaining snapshot refers to.
+    pub async fn prune(&self) -> Result<PruneReport> {
+        let _exclusive = self.prune_lock.write().await;
+        let mut report = PruneReport::default();
+
+        let mut by_workspace: HashMap<(String, String), Vec<Snapshot>> = HashMap::new();
+        for key in self.storage.list(&format!("{PREFIX}/snapshots/")).await? {
+            if let Some(snapshot) = self.read_snapshot(&key).await? {
+                by_workspace
+                    .entry((snapshot.user_id.clone(), snapshot.workspace.clone()))
+                    .or_default()
+                    .push(snapshot);
+            }
+        }
+
+        let mut trees = HashSet::new();
+        for snapshots in by_workspace.values() {
+            let keep = retained(snapshots, &self.config.retention);
+            for snapshot in snapshots {
+                if keep.contains(&snapshot.id) {
+                    trees.insert(snapshot.tree.clone());
+                } else {
+                    self.delete(snapshot).await?;
+                    report.snapshots_removed += 1;
+                }
+            }
+        }
+
+        let mut chunks = HashSet::new();
+        for hash in &trees {
+            let data = self
+                .storage
+                .get(&tree_key(hash))
+                .await?
+                .ok_or_else(|| anyhow!("tree {hash} is missing"))?;
+            let tree: Tree = serde_json::from_slice(&data).context("parsing snapshot tree")?;
+            chunks.extend(tree.entries.into_iter().flat_map(|entry| entry.chunks));
+        }
+
+        for key in self.storage.list(&format!("{PREFIX}/trees/")).await? {
+            let hash = key.rsplit('/').next().unwrap_or_default();
+            if !trees.contains(hash.trim_end_matches(".json")) {
+                self.storage.delete(&key).await?;
+                report.trees_removed += 1;
+            }
+        }
+        for key in self.storage.list(&format!("{PREFIX}/chunks/")).await? {
+            let hash = key.rsplit('/').next().unwrap_or_default();
+            if chunks.contains(hash) {
+                report.chunks_kept += 1;
+            } else {
+                self.storage.delete(&key).await?;
+                report.chunks_removed += 1;
+            }
+        }
+        info!(
+            snapshots_removed = report.snapshots_removed,
+            trees_removed = report.trees_removed,
+            chunks_removed = report.chunks_removed,
+            chunks_kept = report.chunks_kept,
+            "Pruned workspace backups"
+        );
+        Ok(report)
+    }
+}
+
+/// IDs of the snapshots `policy` keeps.
+pub fn retained(snapshots: &[Snapshot], policy: &RetentionPolicy) -> HashSet<String> {
+    let mut newest_first: Vec<&Snapshot> = snapshots.iter().collect();
+    newest_first.sort_by(|a, b| b.created_at.cmp(&a.created_at).then(b.id.cmp(&a.id)));
+    if policy.keep_last + policy.keep_daily + policy.keep_weekly + policy.keep_monthly == 0 {
+        return newest_first.iter().map(|s| s.id.clone()).collect();
+    }
+
+    let mut keep: HashSet<String> = newest_first
+        .iter()
+        .take(policy.keep_last)
+        .map(|s| s.id.clone())
+        .collect();
+    let buckets: [(usize, fn(&DateTime<Utc>) -> (i32, u32)); 3] = [
+        (policy.keep_daily, |t| (t.year(), t.ordinal())),
+        (policy.keep_weekly, |t| {
+            let week = t.iso_week();
+            (week.year(), week.week())
+        }),
+        (policy.keep_monthly, |t| (t.year(), t.month())),
+    ];
+    for (count, bucket_of) in buckets {
+        let mut seen = Vec::new();
+        for snapshot in &newest_first {
+            let bucket = bucket_of(&snapshot.created_at);
+            if seen.last() == Some(&bucket) {
+                continue;
+            }
+            if seen.len() == count {
+                break;
+            }
+            seen.push(bucket);
+            keep.insert(snapshot.id.clone());
+        }
+    }
+    keep
+}
+
+/// `path` as a relative path without `..`, or None.
+fn safe_relative(path: &str) -> Option<PathBuf> {
+    let path = Path::new(path);
+    path.components()
+        .all(|c| matches!(c, Component::Normal(_)))
+        .then(|| path.to_path_buf())
+}
+
+enum Scanned {
+    Chunk { hash: String, data: Vec<u8> },
+    Entry(TreeEntry),
+}
+
+/// Walk `dir` and send its entries, and the chunks of files that changed
+/// since `previous`, to `tx`. Symlinks are recorded, not followed.
+fn scan(
+    dir: &Path,
+    exclude: &[String],
+    previous: &HashMap<String, TreeEntry>,
+    tx: &mpsc::Sender<Scanned>,
+) -> Result<()> {
+    let rules = ExclusionRules::load(dir, exclude);
+    for item in rules.walk(dir, dir, |_, _| {}) {
+        if item.depth() == 0 {
+            continue;
+        }
+        let Some(path) = item
+            .path()
+            .strip_prefix(dir)
+            .ok()
+            .and_then(|relative| relative.to_str())
+            .map(str::to_string)
+        else {
+            debug!(path = %item.path().display(), "Skipping path that is not UTF-8");
+            continue;
+        };
+        // Paths can go away during the walk.
+        let Ok(metadata) = item.path().symlink_metadata() else {
+            continue;
+        };
+        let mode = metadata.permissions().mode() & 0o7777;
+        let file_type = item.file_type();
+        let mut entry = TreeEntry {
+            path,
+            kind: EntryKind::File,
+            mode,
+            size: 0,
+            mtime: 0,
+            chunks: Vec::new(),
+            target: None,
+        };
+        if file_type.is_dir() {
+            entry.kind = EntryKind::Dir;
+        } else if file_type.is_symlink() {
+            let Ok(target) = std::fs::read_link(item.path()) else {
+                continue;
+            };
+            entry.kind = EntryKind::Symlink;
+            entry.target = Some(target.to_string_lossy().into_owned());
+        } else if file_type.is_file() {
+            entry.mtime = metadata.mtime();
+            match previous.get(&entry.path) {
+                Some(prev) if prev.size == metadata.len() && prev.mtime == entry.mtime => {
+                    entry.size = prev.size;
+                    entry.chunks = prev.chunks.clone();
+                }
+                _ => match chunk_file(item.path(), tx) {
+                    Ok((chunks, size)) => {
+                        entry.chunks = chunks;
+                        entry.size = size;
+                    }
+                    Err(e)
+                        if e.downcast_ref::<std::io::Error>()
+                            .is_some_and(|e| e.kind() == std::io::ErrorKind::NotFound) =>
+                    {
+                        continue;
+                    }
+                    Err(e) => return Err(e),
+                },
+            }
+        } else {
+            // Sockets, FIFOs and devices.
+            continue;
+        }
+        if tx.blocking_send(Scanned::Entry(entry)).is_err() {
+            bail!("backup cancelled");
+        }
+    }
+    Ok(())
+}
+
+/// Chunk a file into `tx`. Returns its chunk addresses and size.
+fn chunk_file(path: &Path, tx: &mpsc::Sender<Scanned>) -> Result<(Vec<String>, u64)> {
+    let mut chunker = Chunker::new(std::fs::File::open(path)?);
+    let (mut chunks, mut size) = (Vec::new(), 0);
+    while let Some(data) = chunker
+        .next_chunk()
+        .with_context(|| format!("reading {}", path.display()))?
+    {
+        let hash = chunk_hash(&data);
+        size += data.len() as u64;
+        chunks.push(hash.clone());
+        if tx.blocking_send(Scanned::Chunk { hash, data }).is_err() {
+            bail!("backup cancelled");
+        }
+    }
+    Ok((chunks, size))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::LocalStorage;
+    use chrono::TimeZone;
+
+    fn snapshot(id: &str, created_at: DateTime<Utc>) -> Snapshot {
+        Snapshot {
+            id: id.to_string(),
+            user_id: "u1".to_string(),
+            workspace: "ws".to_string(),
+            created_at,
+            tree: String::new(),
+            files: 0,
+            bytes: 0,
+            new_chunks: 0,
+            new_bytes: 0,
+            parent: None,
+        }
+    }
+
+    #[test]
+    fn test_retention_keeps_newest_per_bucket() {
+        // Two snapshots a day for ten days in March 2026.
+        let snapshots: Vec<Snapshot> = (1..=10)
+            .flat_map(|day| {
+                [6, 18].map(|hour| {
+                    snapshot(
+                        &format!("d{day:02}h{hour:02}"),
+                        Utc.with_ymd_and_hms(2026, 3, day, hour, 0, 0).unwrap(),
+                    )
+                })
+            })
+            .collect();
+        let policy = RetentionPolicy {
+            keep_last: 1,
+            keep_daily: 3,
+            keep_weekly: 2,
+            keep_monthly: 0,
+        };
+        let mut keep: Vec<String> = retained(&snapshots, &policy).into_iter().collect();
+        keep.sort();
+        // Last: d10h18. Daily: d10h18, d09h18, d08h18. Weekly (ISO weeks
+        // start on Monday, March 9 and March 2): d10h18, d08h18.
+        assert_eq!(keep, vec!["d08h18", "d09h18", "d10h18"]);
+
+        let everything = RetentionPolicy {
+            keep_last: 0,
+            keep_daily: 0,
+            keep_weekly: 0,
+            keep_monthly: 0,
+        };
+        assert_eq!(retained(&snapshots, &everything).len(), snapshots.len());
+    }
+
+    #[test]
+    fn test_snapshot_ids_and_paths() {
+        let id = new_snapshot_id(Utc.with_ymd_and_hms(2026, 10, 15, 12, 0, 0).unwrap());
+        assert!(id.starts_with("20261015T120000Z-"));
+        assert!(valid_snapshot_id(&id));
+        assert!(!valid_snapshot_id("../x"));
+        assert!(!valid_snapshot_id(""));
+
+        assert!(safe_relative("src/main.rs").is_some());
+        assert!(safe_relative("../etc/passwd").is_none());
+        assert!(safe_relative("/etc/passwd").is_none());
+    }
+
+    #[tokio::test]
+    async fn test_backup_dedupes_and_restores() {
+        let store = tempfile::tempdir().unwrap();
+        let workspace = tempfile::tempdir().unwrap();
+        let service = WorkspaceBackupService::new(
+            WorkspaceBackupsConfig {
+                retention: RetentionPolicy {
+                    keep_last: 1,
+                    keep_daily: 0,
+                    keep_weekly: 0,
+                    keep_monthly: 0,
+                },
+                ..Default::default()
+            },
+            Arc::new(LocalStorage::new(store.path())),
+        );
+        let dir = workspace.path();
+        std::fs::create_dir_all(dir.join("node_modules/a")).unwrap();
+        std::fs::create_dir_all(dir.join("node_modules/b")).unwrap();
+        std::fs::write(dir.join("node_modules/a/index.js"), "module.exports = 1;\n").unwrap();
+        std::fs::write(dir.join("node_modules/b/index.js"), "module.exports = 1;\n").unwrap();
+        std::fs::write(dir.join("notes.md"), "first\n").unwrap();
+        std::os::unix::fs::symlink("notes.md", dir.join("link")).unwrap();
+
+        let first = service.backup("u1", "ws", dir).await.unwrap();
+        assert!(!first.unchanged);
+        assert_eq!(first.snapshot.files, 3);
+        // The two identical files share a chunk.
+        assert_eq!(first.snapshot.new_chunks, 2);
+
+        let again = service.backup("u1", "ws", dir).await.unwrap();
+        assert!(again.unchanged);
+        assert_eq!(again.snapshot.id, first.snapshot.id);
+
+        std::fs::write(dir.join("notes.md"), "second version\n").unwrap();
+        let second = service.backup("u1", "ws", dir).await.unwrap();
+        assert!(!second.unchanged);
+        assert_eq!(second.snapshot.new_chunks, 1);
+        assert_eq!(second.snapshot.parent.as_deref(), Some(&*first.snapshot.id));
+        assert_eq!(service.list("u1", Some("ws")).await.unwrap().len(), 2);
+
+        let at_first = service
+            .resolve("u1", "ws", None, Some(first.snapshot.created_at))
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(at_first.id, first.snapshot.id);
+        let restored = tempfile::tempdir().unwrap();
+        let (files, _) = service
+            .restore(&at_first, restored.path(), &[])
+            .await
+            .unwrap();
+        assert_eq!(files, 3);
+        assert_eq!(
+            std::fs::read_to_string(restored.path().join("notes.md")).unwrap(),
+            "first\n"
+        );
+        assert_eq!(
+            std::fs::read_link(restored.path().join("link")).unwrap(),
+            Path::new("notes.md")
+        );
+
+        let partial = tempfile::tempdir().unwrap();
+        let (files, _) = service
+            .restore(&at_first, partial.path(), &["node_modules/a".to_string()])
+            .await
+            .unwrap();
+        assert_eq!(files, 1);
+        assert!(!partial.path().join("notes.md").exists());
+
+        let report = service.prune().await.unwrap();
+        assert_eq!(report.snapshots_removed, 1);
+        assert_eq!(report.trees_removed, 1);
+        assert_eq!(report.chunks_removed, 1, "only the old notes.md chunk goes");
+        assert_eq!(report.chunks_kept, 2);
+        assert_eq!(
+            service.list("u1", None).await.unwrap(),
+            vec![second.snapshot]
+        );
+    }
+}
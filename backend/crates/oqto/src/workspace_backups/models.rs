@@ -0,0 +1,193 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+/// `[workspace_backups]` configuration.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WorkspaceBackupsConfig {
+    /// Back up every workspace on a schedule and serve the backup API.
+    pub enabled: bool,
+    /// Hours between snapshots of a workspace.
+    pub interval_hours: u64,
+    /// Globs left out of snapshots, on top of each workspace's
+    /// `.oqtoignore`.
+    pub exclude: Vec<String>,
+    pub retention: RetentionPolicy,
+}
+
+impl Default for WorkspaceBackupsConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            interval_hours: 24,
+            exclude: Vec::new(),
+            retention: RetentionPolicy::default(),
+        }
+    }
+}
+
+/// Which snapshots of a workspace survive a prune. A snapshot is kept if
+/// any rule keeps it; with every rule at 0 all snapshots are kept.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RetentionPolicy {
+    /// The newest snapshots.
+    pub keep_last: usize,
+    /// The newest snapshot of each of the last days that have one.
+    pub keep_daily: usize,
+    /// Same per ISO week.
+    pub keep_weekly: usize,
+    /// Same per calendar month.
+    pub keep_monthly: usize,
+}
+
+impl Default for RetentionPolicy {
+    fn default() -> Self {
+        Self {
+            keep_last: 3,
+            keep_daily: 7,
+            keep_weekly: 4,
+            keep_monthly: 6,
+        }
+    }
+}
+
+/// A workspace at one point in time.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Snapshot {
+    /// Sorts by creation time.
+    pub id: String,
+    pub user_id: String,
+    pub workspace: String,
+    pub created_at: DateTime<Utc>,
+    /// Address of the [`Tree`].
+    pub tree: String,
+    /// Files and their total size.
+    pub files: u64,
+    pub bytes: u64,
+    /// Chunks and bytes this snapshot added to the store; everything else
+    /// was already there.
+    pub new_chunks: u64,
+    pub new_bytes: u64,
+    /// Snapshot the unchanged files were taken from.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub parent: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum EntryKind {
+    File,
+    Dir,
+    Symlink,
+}
+
+/// A path in a snapshot.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TreeEntry {
+    /// `/`-separated path from the workspace directory.
+    pub path: String,
+    pub kind: EntryKind,
+    /// Permission bits.
+    pub mode: u32,
+    #[serde(default)]
+    pub size: u64,
+    /// Modification time (seconds); unchanged size and mtime mean the
+    /// file is not read again.
+    #[serde(default)]
+    pub mtime: i64,
+    /// Chunk addresses, in order (files).
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub chunks: Vec<String>,
+    /// Link target (symlinks).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub target: Option<String>,
+}
+
+/// Every path of a snapshot, sorted. Stored content-addressed, so a
+/// workspace that did not change does not add a tree.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Tree {
+    pub entries: Vec<TreeEntry>,
+}
+
+/// Outcome of a backup run.
+#[derive(Debug, Clone, Serialize)]
+pub struct BackupResult {
+    pub snapshot: Snapshot,
+    /// Nothing changed since `snapshot`, which is the previous one.
+    pub unchanged: bool,
+}
+
+/// A snapshot with its paths.
+#[derive(Debug, Clone, Serialize)]
+pub struct SnapshotDetail {
+    #[serde(flatten)]
+    pub snapshot: Snapshot,
+    pub entries: Vec<SnapshotEntry>,
+}
+
+/// A path as listed to clients.
+#[derive(Debug, Clone, Serialize)]
+pub struct SnapshotEntry {
+    pub path: String,
+    pub kind: EntryKind,
+    pub size: u64,
+    pub modified_at: Option<DateTime<Utc>>,
+}
+
+/// Query of the snapshot list.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct SnapshotQuery {
+    /// Only this workspace's snapshots.
+    pub workspace: Option<String>,
+}
+
+/// Body of a backup request.
+#[derive(Debug, Clone, Deserialize)]
+pub struct CreateBackupRequest {
+    pub workspace: String,
+}
+
+/// Body of a restore.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct RestoreRequest {
+    /// Snapshot to restore.
+    #[serde(default)]
+    pub snapshot_id: Option<String>,
+    /// Or the workspace as it was at this time: the newest snapshot taken
+    /// at or before it.
+    #[serde(default)]
+    pub at: Option<DateTime<Utc>>,
+    /// Workspace to restore into (default `<workspace>-restored-<time>`).
+    /// It must not exist yet.
+    #[serde(default)]
+    pub target: Option<String>,
+    /// Write the snapshot over the workspace itself. Files added since are
+    /// left alone.
+    #[serde(default)]
+    pub in_place: bool,
+    /// Only these paths (and what is below them); empty restores all.
+    #[serde(default)]
+    pub paths: Vec<String>,
+}
+
+/// Outcome of a restore.
+#[derive(Debug, Clone, Serialize)]
+pub struct RestoreResult {
+    pub snapshot_id: String,
+    /// Workspace the files were written to.
+    pub workspace: String,
+    pub files: u64,
+    pub bytes: u64,
+}
+
+/// Outcome of a prune.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct PruneReport {
+    pub snapshots_removed: usize,
+    pub trees_removed: usize,
+    pub chunks_removed: usize,
+    /// Chunks still referenced by a snapshot.
+    pub chunks_kept: usize,
+}
@@ -4,7 +4,11 @@
 //! between frontend clients and backend agent runtimes.
 
 pub mod hub;
+pub mod reconnect;
+pub mod replay;
 pub mod types;
 
 pub use hub::WsHub;
+pub use reconnect::{ClientKind, ReconnectConfig, ReconnectGovernor};
+pub use replay::{EventReplayConfig, SessionStreams};
 pub use types::{UiSpotlightStep, WsEvent};
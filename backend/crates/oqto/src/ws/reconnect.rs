@@ -0,0 +1,422 @@
+//! Reconnect storm protection.
+//!
+//! After a restart every client reconnects at once, and each one resumes
+//! its sessions: the runner session is re-created, its events subscribed
+//! and whatever the client missed replayed. Three things spread that out:
+//!
+//! - Sockets closed on shutdown get close code 1012 (service restart) with a
+//!   jittered `retry_after_ms` in the reason, so clients come back over a
+//!   window instead of in the same second. Dashboards are told to wait
+//!   longer than interactive clients.
+//! - Resumes (`session.create` with `last_seen_seq`) take a token from a
+//!   shared bucket. Without one they queue; when the queue is full or the
+//!   wait too long they are turned away with a retry hint.
+//! - Queued resumes of interactive clients go before those of dashboards
+//!   (sockets opened with `?client=dashboard`).
+
+use std::collections::VecDeque;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use tokio::sync::{Notify, watch};
+
+/// Close code of sockets closed because the server is restarting.
+pub const WS_CLOSE_SERVICE_RESTART: u16 = 1012;
+
+/// `[reconnect]` config section.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ReconnectConfig {
+    /// Rate-limit session resumes.
+    pub enabled: bool,
+    /// Resumes started per second, sustained.
+    pub replays_per_sec: u32,
+    /// Resumes that can start at once after a quiet period.
+    pub replay_burst: u32,
+    /// Resumes waiting for a token; more are turned away.
+    pub max_queued: usize,
+    /// Longest a resume waits in the queue before it is turned away.
+    pub max_wait_secs: u64,
+    /// Shortest reconnect delay suggested to clients.
+    pub min_delay_ms: u64,
+    /// Longest reconnect delay suggested to clients.
+    pub max_delay_ms: u64,
+}
+
+impl Default for ReconnectConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            replays_per_sec: 20,
+            replay_burst: 50,
+            max_queued: 1000,
+            max_wait_secs: 30,
+            min_delay_ms: 1000,
+            max_delay_ms: 30_000,
+        }
+    }
+}
+
+/// What a socket is used for, from `?client=` on connect.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ClientKind {
+    /// A user is looking at the chat.
+    #[default]
+    Interactive,
+    /// Overviews and monitors that watch many sessions.
+    Dashboard,
+}
+
+impl ClientKind {
+    fn index(self) -> usize {
+        match self {
+            Self::Interactive => 0,
+            Self::Dashboard => 1,
+        }
+    }
+}
+
+/// A resume was turned away; the client should retry after the hint.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ReplayBusy {
+    pub retry_after_ms: u64,
+}
+
+/// Current limiter state.
+#[derive(Debug, Clone, Serialize)]
+pub struct ReconnectStats {
+    pub enabled: bool,
+    pub shutting_down: bool,
+    /// Resumes that can start right now.
+    pub tokens: u32,
+    pub queued_interactive: usize,
+    pub queued_dashboard: usize,
+    /// Resumes let through and turned away since startup.
+    pub admitted: u64,
+    pub rejected: u64,
+}
+
+struct Bucket {
+    tokens: f64,
+    refilled: Instant,
+    next_ticket: u64,
+    /// Waiting tickets by client kind, oldest first.
+    queues: [VecDeque<u64>; 2],
+}
+
+impl Bucket {
+    fn refill(&mut self, now: Instant, rate: f64, burst: f64) {
+        let elapsed = now.saturating_duration_since(self.refilled).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * rate).min(burst);
+        self.refilled = now;
+    }
+
+    fn queued(&self) -> usize {
+        self.queues.iter().map(VecDeque::len).sum()
+    }
+
+    /// Whether nobody queued ahead of `ticket` (None: a new arrival).
+    /// Dashboards also wait for every queued interactive client.
+    fn is_next(&self, kind: ClientKind, ticket: Option<u64>) -> bool {
+        if kind == ClientKind::Dashboard && !self.queues[0].is_empty() {
+            return false;
+        }
+        self.queues[kind.index()].front().copied() == ticket
+    }
+
+    /// Take a token if it is this caller's turn; dequeues `ticket`.
+    fn take(&mut self, kind: ClientKind, ticket: Option<u64>) -> bool {
+        if self.tokens < 1.0 || !self.is_next(kind, ticket) {
+            return false;
+        }
+        self.tokens -= 1.0;
+        if ticket.is_some() {
+            self.queues[kind.index()].pop_front();
+        }
+        true
+    }
+}
+
+/// Paces session resumes and hands out reconnect hints.
+pub struct ReconnectGovernor {
+    config: ReconnectConfig,
+    bucket: Mutex<Bucket>,
+    /// A queued ticket left (admitted, timed out or cancelled).
+    changed: Notify,
+    shutdown: watch::Sender<bool>,
+    admitted: AtomicU64,
+    rejected: AtomicU64,
+}
+
+impl ReconnectGovernor {
+    pub fn new(config: ReconnectConfig) -> Self {
+        let burst = config.replay_burst.max(1) as f64;
+        Self {
+            config,
+            bucket: Mutex::new(Bucket {
+                tokens: burst,
+                refilled: Instant::now(),
+                next_ticket: 0,
+                queues: [VecDeque::new(), VecDeque::new()],
+            }),
+            changed: Notify::new(),
+            shutdown: watch::Sender::new(false),
+            admitted: AtomicU64::new(0),
+            rejected: AtomicU64::new(0),
+        }
+    }
+
+    pub fn config(&self) -> &ReconnectConfig {
+        &self.config
+    }
+
+    fn rate(&self) -> f64 {
+        self.config.replays_per_sec.max(1) as f64
+    }
+
+    fn burst(&self) -> f64 {
+        self.config.replay_burst.max(1) as f64
+    }
+
+    /// Wait for a turn to resume a session. Interactive clients are served
+    /// first; a full queue or a wait past `max_wait_secs` turns the caller
+    /// away.
+    pub async fn admit(&self, kind: ClientKind) -> Result<(), ReplayBusy> {
+        if !self.config.enabled {
+            return Ok(());
+        }
+        let deadline = Instant::now() + Duration::from_secs(self.config.max_wait_secs);
+        let ticket = {
+            let mut bucket = self.bucket.lock().unwrap();
+            bucket.refill(Instant::now(), self.rate(), self.burst());
+            if bucket.take(kind, None) {
+                self.admitted.fetch_add(1, Ordering::Relaxed);
+                return Ok(());
+            }
+            let queued = bucket.queued();
+            if queued >= self.config.max_queued {
+                self.rejected.fetch_add(1, Ordering::Relaxed);
+                return Err(self.busy(kind, queued));
+            }
+            let ticket = bucket.next_ticket;
+            bucket.next_ticket += 1;
+            bucket.queues[kind.index()].push_back(ticket);
+            ticket
+        };
+        let _queued = QueuedTicket {
+            governor: self,
+            kind,
+            ticket,
+        };
+
+        loop {
+            let (wait, queued) = {
+                let mut bucket = self.bucket.lock().unwrap();
+                bucket.refill(Instant::now(), self.rate(), self.burst());
+                if bucket.take(kind, Some(ticket)) {
+                    self.admitted.fetch_add(1, Ordering::Relaxed);
+                    return Ok(());
+                }
+                // The next in line sleeps until the next token; everyone
+                // else until someone leaves the queue.
+                let wait = if bucket.is_next(kind, Some(ticket)) {
+                    Duration::from_secs_f64((1.0 - bucket.tokens).max(0.0) / self.rate())
+                } else {
+                    Duration::from_secs(1)
+                };
+                (wait, bucket.queued())
+            };
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            if remaining.is_zero() {
+                self.rejected.fetch_add(1, Ordering::Relaxed);
+                return Err(self.busy(kind, queued));
+            }
+            let _ = tokio::time::timeout(wait.min(remaining), self.changed.notified()).await;
+        }
+    }
+
+    /// Retry hint for a turned-away resume: long enough for the queue to
+    /// drain, plus the client kind's reconnect delay.
+    fn busy(&self, kind: ClientKind, queued: usize) -> ReplayBusy {
+        let drain_ms = (queued as f64 / self.rate() * 1000.0) as u64;
+        ReplayBusy {
+            retry_after_ms: drain_ms + self.reconnect_delay(kind).as_millis() as u64,
+        }
+    }
+
+    /// Range reconnect delays of a client kind are drawn from: interactive
+    /// clients get the first half of `min_delay_ms..max_delay_ms`,
+    /// dashboards the second.
+    pub fn delay_window(&self, kind: ClientKind) -> (u64, u64) {
+        let min = self.config.min_delay_ms;
+        let max = self.config.max_delay_ms.max(min);
+        let mid = min + (max - min) / 2;
+        match kind {
+            ClientKind::Interactive => (min, mid),
+            ClientKind::Dashboard => (mid, max),
+        }
+    }
+
+    /// A random reconnect delay from the kind's window.
+    pub fn reconnect_delay(&self, kind: ClientKind) -> Duration {
+        let (low, high) = self.delay_window(kind);
+        Duration::from_millis(rand::rng().random_range(low..=high))
+    }
+
+    /// Reason of the close frame sent on shutdown: `{"retry_after_ms": N}`
+    /// (well within the 123 bytes a close reason may have).
+    pub fn restart_reason(&self, kind: ClientKind) -> String {
+        serde_json::json!({ "retry_after_ms": self.reconnect_delay(kind).as_millis() as u64 })
+            .to_string()
+    }
+
+    /// Tell every socket to close with a reconnect hint.
+    pub fn begin_shutdown(&self) {
+        self.shutdown.send_replace(true);
+    }
+
+    /// Becomes true when the server starts shutting down.
+    pub fn subscribe_shutdown(&self) -> watch::Receiver<bool> {
+        self.shutdown.subscribe()
+    }
+
+    pub fn snapshot(&self) -> ReconnectStats {
+        let mut bucket = self.bucket.lock().unwrap();
+        bucket.refill(Instant::now(), self.rate(), self.burst());
+        ReconnectStats {
+            enabled: self.config.enabled,
+            shutting_down: *self.shutdown.borrow(),
+            tokens: bucket.tokens as u32,
+            queued_interactive: bucket.queues[0].len(),
+            queued_dashboard: bucket.queues[1].len(),
+            admitted: self.admitted.load(Ordering::Relaxed),
+            rejected: self.rejected.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// A place in the queue, given up on drop (admitted, timed out, or the
+/// socket went away while waiting).
+struct QueuedTicket<'a> {
+    governor: &'a ReconnectGovernor,
+    kind: ClientKind,
+    ticket: u64,
+}
+
+impl Drop for QueuedTicket<'_> {
+    fn drop(&mut self) {
+        self.governor.bucket.lock().unwrap().queues[self.kind.index()]
+            .retain(|ticket| *ticket != self.ticket);
+        self.governor.changed.notify_waiters();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+
+    fn governor(configure: impl FnOnce(&mut ReconnectConfig)) -> Arc<ReconnectGovernor> {
+        let mut config = ReconnectConfig {
+            replays_per_sec: 1,
+            replay_burst: 1,
+            max_queued: 10,
+            max_wait_secs: 30,
+            ..Default::default()
+        };
+        configure(&mut config);
+        Arc::new(ReconnectGovernor::new(config))
+    }
+
+    #[test]
+    fn test_delay_windows_split_by_client_kind() {
+        let governor = governor(|c| {
+            c.min_delay_ms = 1000;
+            c.max_delay_ms = 5000;
+        });
+        assert_eq!(governor.delay_window(ClientKind::Interactive), (1000, 3000));
+        assert_eq!(governor.delay_window(ClientKind::Dashboard), (3000, 5000));
+        for _ in 0..50 {
+            let delay = governor.reconnect_delay(ClientKind::Dashboard).as_millis();
+            assert!((3000..=5000).contains(&delay));
+        }
+
+        let reason: serde_json::Value =
+            serde_json::from_str(&governor.restart_reason(ClientKind::Interactive)).unwrap();
+        let retry = reason["retry_after_ms"].as_u64().unwrap();
+        assert!((1000..=3000).contains(&retry));
+    }
+
+    #[tokio::test]
+    async fn test_interactive_resumes_go_before_dashboards() {
+        let governor = governor(|c| c.replays_per_sec = 20);
+        governor.admit(ClientKind::Dashboard).await.unwrap();
+
+        let order = Arc::new(Mutex::new(Vec::new()));
+        let dashboard = {
+            let (governor, order) = (Arc::clone(&governor), Arc::clone(&order));
+            tokio::spawn(async move {
+                governor.admit(ClientKind::Dashboard).await.unwrap();
+                order.lock().unwrap().push(ClientKind::Dashboard);
+            })
+        };
+        tokio::task::yield_now().await;
+        let interactive = {
+            let (governor, order) = (Arc::clone(&governor), Arc::clone(&order));
+            tokio::spawn(async move {
+                governor.admit(ClientKind::Interactive).await.unwrap();
+                order.lock().unwrap().push(ClientKind::Interactive);
+            })
+        };
+        tokio::task::yield_now().await;
+        let stats = governor.snapshot();
+        assert_eq!((stats.queued_interactive, stats.queued_dashboard), (1, 1));
+
+        interactive.await.unwrap();
+        dashboard.await.unwrap();
+        assert_eq!(
+            *order.lock().unwrap(),
+            vec![ClientKind::Interactive, ClientKind::Dashboard]
+        );
+        assert_eq!(governor.snapshot().admitted, 3);
+    }
+
+    #[tokio::test]
+    async fn test_full_or_slow_queue_turns_resumes_away() {
+        let governor = governor(|c| {
+            c.max_queued = 1;
+            c.max_wait_secs = 0;
+        });
+        governor.admit(ClientKind::Interactive).await.unwrap();
+        let busy = governor.admit(ClientKind::Interactive).await.unwrap_err();
+        assert!(busy.retry_after_ms >= governor.config().min_delay_ms);
+        let stats = governor.snapshot();
+        assert_eq!(stats.rejected, 1);
+        assert_eq!(
+            stats.queued_interactive, 0,
+            "turned away callers leave the queue"
+        );
+
+        let disabled = self::governor(|c| {
+            c.enabled = false;
+            c.max_queued = 0;
+        });
+        for _ in 0..5 {
+            disabled.admit(ClientKind::Dashboard).await.unwrap();
+        }
+    }
+
+    #[tokio::test]
+    async fn test_shutdown_notifies_subscribers() {
+        let governor = governor(|_| {});
+        let mut shutdown = governor.subscribe_shutdown();
+        assert!(!*shutdown.borrow());
+        governor.begin_shutdown();
+        shutdown.wait_for(|stopping| *stopping).await.unwrap();
+        assert!(governor.snapshot().shutting_down);
+    }
+}
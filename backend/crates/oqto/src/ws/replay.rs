@@ -0,0 +1,423 @@
+//! Shared agent event streams with replay for reconnecting clients.
+//!
+//! Each WebSocket connection used to hold its own runner subscription, so
+//! whatever the agent emitted while a client was reconnecting was lost. With
+//! replay enabled, one runner subscription per session records into an
+//! [`EventLog`] and fans out to every connection watching the session. When
+//! the last connection detaches, the stream is kept for `retain_secs`; a
+//! client that comes back passes the last `seq` it saw as `last_seen_seq`
+//! in `session.create` and gets the missed events before live ones. If they
+//! were evicted in the meantime it gets `stream.resync_required` instead.
+
+use std::collections::{HashMap, VecDeque};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+use tokio::sync::broadcast;
+use tracing::{debug, info};
+
+use oqto_protocol::events::{Event, EventPayload};
+use oqto_protocol::replay::{EventLog, Replay};
+use oqto_runner::client::{PiSubscription, PiSubscriptionEvent, RunnerClient};
+
+/// Live events buffered per attached connection before it has to catch up
+/// from the log.
+const FANOUT_BUFFER: usize = 1024;
+
+/// Event replay configuration.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct EventReplayConfig {
+    /// Share runner subscriptions between connections and replay missed
+    /// events on reconnect.
+    pub enabled: bool,
+    /// Events retained per session.
+    pub buffer_events: usize,
+    /// Keep a session's stream this long after its last connection left.
+    pub retain_secs: u64,
+}
+
+impl Default for EventReplayConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            buffer_events: 2000,
+            retain_secs: 300,
+        }
+    }
+}
+
+/// One session's runner subscription, log and listeners.
+struct SessionStream {
+    log: Mutex<EventLog>,
+    tx: broadcast::Sender<PiSubscriptionEvent>,
+    listeners: AtomicUsize,
+    /// When the last listener detached (None while someone is attached).
+    idle_since: Mutex<Option<Instant>>,
+    ended: AtomicBool,
+}
+
+impl SessionStream {
+    fn is_ended(&self) -> bool {
+        self.ended.load(Ordering::SeqCst)
+    }
+}
+
+/// Per-session shared event streams.
+pub struct SessionStreams {
+    config: EventReplayConfig,
+    streams: Mutex<HashMap<String, Arc<SessionStream>>>,
+}
+
+impl SessionStreams {
+    pub fn new(config: EventReplayConfig) -> Self {
+        Self {
+            config,
+            streams: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Attach to a session's events, subscribing on `runner` if no stream is
+    /// running. With `last_seen_seq`, retained events after it are yielded
+    /// first. `runner_id` is used for events synthesized here.
+    pub async fn attach(
+        self: &Arc<Self>,
+        runner: &RunnerClient,
+        runner_id: &str,
+        session_id: &str,
+        last_seen_seq: Option<u64>,
+    ) -> anyhow::Result<StreamAttachment> {
+        let stream = self.stream(runner, session_id).await?;
+        stream.listeners.fetch_add(1, Ordering::SeqCst);
+        *stream.idle_since.lock().unwrap() = None;
+
+        // Subscribe and snapshot under the log lock so no event is both
+        // replayed and received live, or neither.
+        let (rx, replay) = {
+            let log = stream.log.lock().unwrap();
+            let rx = stream.tx.subscribe();
+            let replay = match last_seen_seq {
+                Some(seq) if seq <= log.last_seq() => log.since(Some(seq), usize::MAX),
+                // A cursor from an earlier stream (expired, or the backend
+                // restarted): whatever happened in between is unknown.
+                Some(_) => Replay {
+                    events: Vec::new(),
+                    next_cursor: log.last_seq(),
+                    missed: true,
+                },
+                None => Replay {
+                    events: Vec::new(),
+                    next_cursor: log.last_seq(),
+                    missed: false,
+                },
+            };
+            (rx, replay)
+        };
+
+        let mut attachment = StreamAttachment {
+            stream,
+            rx,
+            pending: VecDeque::new(),
+            cursor: last_seen_seq.unwrap_or_default(),
+            replayed_until: 0,
+            session_id: session_id.to_string(),
+            runner_id: runner_id.to_string(),
+        };
+        if last_seen_seq.is_some() {
+            debug!(
+                session_id = %session_id,
+                replayed = replay.events.len(),
+                missed = replay.missed,
+                "Replaying agent events"
+            );
+        }
+        attachment.queue(replay);
+        attachment.replayed_until = attachment.cursor;
+        Ok(attachment)
+    }
+
+    async fn stream(
+        self: &Arc<Self>,
+        runner: &RunnerClient,
+        session_id: &str,
+    ) -> anyhow::Result<Arc<SessionStream>> {
+        if let Some(stream) = self.streams.lock().unwrap().get(session_id)
+            && !stream.is_ended()
+        {
+            return Ok(Arc::clone(stream));
+        }
+
+        let subscription = runner.agent_subscribe(session_id).await?;
+        let (tx, _) = broadcast::channel(FANOUT_BUFFER);
+        let stream = Arc::new(SessionStream {
+            log: Mutex::new(EventLog::new(self.config.buffer_events)),
+            tx,
+            listeners: AtomicUsize::new(0),
+            idle_since: Mutex::new(Some(Instant::now())),
+            ended: AtomicBool::new(false),
+        });
+        {
+            let mut streams = self.streams.lock().unwrap();
+            // A concurrent attach may have won the race; keep its stream.
+            if let Some(existing) = streams.get(session_id)
+                && !existing.is_ended()
+            {
+                return Ok(Arc::clone(existing));
+            }
+            streams.insert(session_id.to_string(), Arc::clone(&stream));
+        }
+        debug!(session_id = %session_id, "Started shared agent event stream");
+
+        let streams = Arc::clone(self);
+        let session_id = session_id.to_string();
+        let task_stream = Arc::clone(&stream);
+        tokio::spawn(async move {
+            streams.run(session_id, task_stream, subscription).await;
+        });
+        Ok(stream)
+    }
+
+    async fn run(
+        &self,
+        session_id: String,
+        stream: Arc<SessionStream>,
+        mut subscription: PiSubscription,
+    ) {
+        let retain = Duration::from_secs(self.config.retain_secs.max(1));
+        let mut idle_check = tokio::time::interval(retain / 4 + Duration::from_secs(1));
+        idle_check.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+
+        loop {
+            tokio::select! {
+                next = subscription.next() => match next {
+                    Some(PiSubscriptionEvent::Event(event)) => {
+                        let mut log = stream.log.lock().unwrap();
+                        let event = log.push(*event).clone();
+                        let _ = stream.tx.send(PiSubscriptionEvent::Event(Box::new(event)));
+                    }
+                    Some(end @ (PiSubscriptionEvent::End { .. } | PiSubscriptionEvent::Error { .. })) => {
+                        stream.ended.store(true, Ordering::SeqCst);
+                        let _ = stream.tx.send(end);
+                        break;
+                    }
+                    None => {
+                        stream.ended.store(true, Ordering::SeqCst);
+                        break;
+                    }
+                },
+                _ = idle_check.tick() => {
+                    let idle = stream
+                        .idle_since
+                        .lock()
+                        .unwrap()
+                        .is_some_and(|since| since.elapsed() > retain);
+                    if idle && stream.listeners.load(Ordering::SeqCst) == 0 {
+                        stream.ended.store(true, Ordering::SeqCst);
+                        break;
+                    }
+                }
+            }
+        }
+
+        let mut streams = self.streams.lock().unwrap();
+        if streams
+            .get(&session_id)
+            .is_some_and(|current| Arc::ptr_eq(current, &stream))
+        {
+            streams.remove(&session_id);
+        }
+        info!(session_id = %session_id, "Stopped shared agent event stream");
+    }
+}
+
+/// A connection's view of a session stream: replayed events first, then
+/// live ones.
+pub struct StreamAttachment {
+    stream: Arc<SessionStream>,
+    rx: broadcast::Receiver<PiSubscriptionEvent>,
+    pending: VecDeque<PiSubscriptionEvent>,
+    /// `seq` of the last event yielded.
+    cursor: u64,
+    /// Events up to this `seq` were emitted before the attach.
+    replayed_until: u64,
+    session_id: String,
+    runner_id: String,
+}
+
+impl StreamAttachment {
+    /// Whether `event` was replayed rather than received live. Per-event
+    /// side effects already ran for replayed events when they were live.
+    pub fn is_replayed(&self, event: &Event) -> bool {
+        event.seq.is_none_or(|seq| seq <= self.replayed_until)
+    }
+
+    /// Next event, or None once the stream is gone.
+    pub async fn next(&mut self) -> Option<PiSubscriptionEvent> {
+        loop {
+            if let Some(event) = self.pending.pop_front() {
+                return Some(event);
+            }
+            match self.rx.recv().await {
+                Ok(PiSubscriptionEvent::Event(event)) => {
+                    let seq = event.seq.unwrap_or_default();
+                    if seq <= self.cursor {
+                        continue;
+                    }
+                    self.cursor = seq;
+                    return Some(PiSubscriptionEvent::Event(event));
+                }
+                Ok(end) => return Some(end),
+                // Fell behind the fan-out buffer: catch up from the log.
+                Err(broadcast::error::RecvError::Lagged(_)) => {
+                    let replay = self
+                        .stream
+                        .log
+                        .lock()
+                        .unwrap()
+                        .since(Some(self.cursor), usize::MAX);
+                    self.queue(replay);
+                }
+                Err(broadcast::error::RecvError::Closed) => return None,
+            }
+        }
+    }
+
+    /// Queue replayed events, preceded by `stream.resync_required` if some
+    /// were lost.
+    fn queue(&mut self, replay: Replay) {
+        if replay.missed {
+            let first = replay
+                .events
+                .first()
+                .and_then(|e| e.seq)
+                .unwrap_or(replay.next_cursor + 1);
+            self.pending
+                .push_back(PiSubscriptionEvent::Event(Box::new(Event {
+                    session_id: self.session_id.clone(),
+                    runner_id: self.runner_id.clone(),
+                    ts: chrono::Utc::now().timestamp_millis(),
+                    seq: None,
+                    payload: EventPayload::StreamResyncRequired {
+                        dropped_count: first.saturating_sub(self.cursor + 1),
+                        reason: "events missed while disconnected were evicted".to_string(),
+                    },
+                })));
+        }
+        self.cursor = self.cursor.max(replay.next_cursor);
+        self.pending.extend(
+            replay
+                .events
+                .into_iter()
+                .map(|event| PiSubscriptionEvent::Event(Box::new(event))),
+        );
+    }
+}
+
+impl Drop for StreamAttachment {
+    fn drop(&mut self) {
+        if self.stream.listeners.fetch_sub(1, Ordering::SeqCst) == 1 {
+            *self.stream.idle_since.lock().unwrap() = Some(Instant::now());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn event(n: i64) -> Event {
+        Event {
+            session_id: "ses_1".to_string(),
+            runner_id: "local".to_string(),
+            ts: n,
+            seq: None,
+            payload: EventPayload::AgentIdle {
+                message_version: None,
+            },
+        }
+    }
+
+    fn attachment(log: EventLog, cursor: u64) -> (StreamAttachment, Arc<SessionStream>) {
+        let (tx, rx) = broadcast::channel(4);
+        let stream = Arc::new(SessionStream {
+            log: Mutex::new(log),
+            tx,
+            listeners: AtomicUsize::new(1),
+            idle_since: Mutex::new(None),
+            ended: AtomicBool::new(false),
+        });
+        let attachment = StreamAttachment {
+            stream: Arc::clone(&stream),
+            rx,
+            pending: VecDeque::new(),
+            cursor,
+            replayed_until: 0,
+            session_id: "ses_1".to_string(),
+            runner_id: "local".to_string(),
+        };
+        (attachment, stream)
+    }
+
+    fn seq_of(event: Option<PiSubscriptionEvent>) -> Option<u64> {
+        match event {
+            Some(PiSubscriptionEvent::Event(event)) => event.seq,
+            _ => panic!("expected an event"),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_attachment_replays_then_skips_duplicates() {
+        let mut log = EventLog::new(10);
+        for n in 0..3 {
+            log.push(event(n));
+        }
+        let replay = log.since(Some(1), usize::MAX);
+        let (mut attachment, stream) = attachment(log, 1);
+        attachment.queue(replay);
+
+        // Event 3 is delivered live as well; it must not be seen twice.
+        let live = stream.log.lock().unwrap().since(Some(2), 1).events;
+        stream
+            .tx
+            .send(PiSubscriptionEvent::Event(Box::new(live[0].clone())))
+            .unwrap();
+        let fourth = stream.log.lock().unwrap().push(event(3)).clone();
+        stream
+            .tx
+            .send(PiSubscriptionEvent::Event(Box::new(fourth)))
+            .unwrap();
+
+        assert_eq!(seq_of(attachment.next().await), Some(2));
+        assert_eq!(seq_of(attachment.next().await), Some(3));
+        assert_eq!(seq_of(attachment.next().await), Some(4));
+
+        drop(attachment);
+        assert_eq!(stream.listeners.load(Ordering::SeqCst), 0);
+        assert!(stream.idle_since.lock().unwrap().is_some());
+    }
+
+    #[tokio::test]
+    async fn test_attachment_reports_evicted_events() {
+        let mut log = EventLog::new(2);
+        for n in 0..5 {
+            log.push(event(n));
+        }
+        let replay = log.since(Some(1), usize::MAX);
+        let (mut attachment, _stream) = attachment(log, 1);
+        attachment.queue(replay);
+
+        match attachment.next().await {
+            Some(PiSubscriptionEvent::Event(event)) => match event.payload {
+                EventPayload::StreamResyncRequired { dropped_count, .. } => {
+                    assert_eq!(dropped_count, 2);
+                }
+                other => panic!("expected resync, got {other:?}"),
+            },
+            _ => panic!("expected an event"),
+        }
+        assert_eq!(seq_of(attachment.next().await), Some(4));
+        assert_eq!(seq_of(attachment.next().await), Some(5));
+    }
+}
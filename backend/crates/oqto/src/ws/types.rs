@@ -279,6 +279,85 @@ pub enum WsEvent {
         detail: Option<Value>,
     },
 
+    // ========== Notifications ==========
+    /// User-facing notification raised by a backend subsystem.
+    Notification {
+        /// "info", "warning" or "error".
+        level: String,
+        title: String,
+        message: String,
+        /// Machine-readable category (e.g. "tool_usage.anomaly").
+        category: String,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        detail: Option<Value>,
+    },
+
+    // ========== Voice Events ==========
+    /// Voice services became available or unavailable.
+    #[serde(rename = "voice.availability")]
+    VoiceAvailability {
+        available: bool,
+        /// Why voice is unavailable.
+        #[serde(skip_serializing_if = "Option::is_none")]
+        reason: Option<String>,
+    },
+
+    // ========== Preview Events ==========
+    /// Ports a session's dev servers listen on changed.
+    #[serde(rename = "preview.ports")]
+    PreviewPorts {
+        session_id: String,
+        /// Ports that can be opened under `/api/sessions/{id}/preview/{port}`.
+        ports: Vec<u16>,
+    },
+
+    // ========== Resource Events ==========
+    /// A new resource usage sample of a running session.
+    #[serde(rename = "session.resources")]
+    SessionResources {
+        session_id: String,
+        sample: crate::session::resources::ResourceSample,
+    },
+
+    // ========== Port Conflict Events ==========
+    /// A session's port was taken by another program on start, so the
+    /// session moved to other ports.
+    #[serde(rename = "session.port_conflict")]
+    SessionPortConflict {
+        session_id: String,
+        /// The taken port, when it could be told which one.
+        #[serde(skip_serializing_if = "Option::is_none")]
+        port: Option<u16>,
+        /// The process holding it, when it could be found.
+        #[serde(skip_serializing_if = "Option::is_none")]
+        holder: Option<crate::session::PortHolder>,
+        /// Agent, fileserver and ttyd ports before and after the move.
+        previous_ports: [i64; 3],
+        ports: [i64; 3],
+        attempt: u32,
+    },
+
+    // ========== Annotation Events ==========
+    /// A message was annotated, or an annotation was removed.
+    #[serde(rename = "message.annotation")]
+    MessageAnnotation {
+        session_id: String,
+        message_id: String,
+        /// The annotation (see `/api/sessions/{id}/annotations`).
+        annotation: Value,
+        removed: bool,
+    },
+    /// A message or part was made private to the session owner, or
+    /// public again. Viewers should reload the session's messages.
+    #[serde(rename = "message.private")]
+    MessagePrivate {
+        session_id: String,
+        message_id: String,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        part_id: Option<String>,
+        private: bool,
+    },
+
     // ========== Legacy Events ==========
     /// Legacy SSE event (deprecated).
     /// Contains the original event type and data.
@@ -3,6 +3,8 @@
 //! Provides administrative commands for managing the Oqto server,
 //! including container management, image refresh, and housekeeping.
 
+mod profiles;
+
 use std::io::{self, IsTerminal, Read, Write};
 use std::path::PathBuf;
 use std::process::ExitCode;
@@ -15,6 +17,8 @@ use hyper_util::client::legacy::Client as HyperClient;
 use hyper_util::rt::TokioExecutor;
 use reqwest::StatusCode;
 
+use profiles::{Profile, ProfileStore, TokenStorage};
+
 #[cfg(unix)]
 use hyperlocal::{UnixConnector, Uri as UnixUri};
 
@@ -32,9 +36,46 @@ fn main() -> ExitCode {
 #[tokio::main]
 async fn try_main() -> Result<()> {
     let cli = Cli::parse();
-    let client = OqtoClient::new(&cli.server, cli.admin_socket.as_deref())?;
+    let command = match cli.command {
+        Command::Login {
+            url,
+            sso,
+            username,
+            password_stdin,
+            token_stdin,
+            expires_in_days,
+        } => {
+            let method = if token_stdin {
+                LoginMethod::Token
+            } else if sso {
+                LoginMethod::Sso
+            } else {
+                LoginMethod::Password {
+                    username,
+                    password_stdin,
+                }
+            };
+            return handle_login(
+                &url,
+                cli.profile.as_deref(),
+                method,
+                expires_in_days,
+                cli.json,
+            )
+            .await;
+        }
+        Command::Logout => return handle_logout(cli.profile.as_deref(), cli.json).await,
+        Command::Profiles { command } => return handle_profiles(command, cli.json),
+        command => command,
+    };
+    let (server, auth_token) = resolve_target(
+        cli.server.as_deref(),
+        cli.profile.as_deref(),
+        cli.admin_socket.is_some(),
+    )?;
+    let client = OqtoClient::new(&server, cli.admin_socket.as_deref(), auth_token)?;
 
-    match cli.command {
+    match command {
         Command::Status => handle_status(&client, cli.json).await,
         Command::Ask {
             target,
@@ -50,6 +91,11 @@ async fn try_main() -> Result<()> {
         Command::Image { command } => handle_image(&client, command, cli.json).await,
         Command::A2ui { command } => handle_a2ui(&client, command, cli.json).await,
         Command::Ui { command } => handle_ui(&client, command, cli.json).await,
+        Command::Bg { command } => handle_bg(&client, command, cli.json).await,
+        Command::Memory { command } => handle_memory(&client, command, cli.json).await,
+        Command::Outbox { command } => handle_outbox(&client, command, cli.json).await,
+        Command::Schedules { command } => handle_schedules(&client, command, cli.json).await,
+        Command::Tokens { command } => handle_tokens(&client, command, cli.json).await,
         Command::Bus { command } => handle_bus(&client, command, cli.json).await,
         Command::Local { command } => handle_local(&client, command, cli.json).await,
         Command::Sandbox { command } => handle_sandbox(command, cli.json).await,
@@ -77,6 +123,9 @@ async fn try_main() -> Result<()> {
             .await
         }
         Command::HashPassword { password, cost } => handle_hash_password(password, cost),
+        Command::Login { .. } | Command::Logout | Command::Profiles { .. } => {
+            unreachable!("handled before connecting")
+        }
     }
 }
 
@@ -88,9 +137,14 @@ async fn try_main() -> Result<()> {
     about = "Control CLI for Oqto server - manage containers, sessions, and images."
 )]
 struct Cli {
-    /// Oqto server URL
-    #[arg(long, short = 's', default_value = DEFAULT_SERVER_URL, env = "OQTO_SERVER_URL")]
-    server: String,
+    /// Oqto server URL [default: the current profile's server, else
+    /// http://localhost:8080/api]
+    #[arg(long, short = 's', env = "OQTO_SERVER_URL")]
+    server: Option<String>,
+
+    /// Saved login to use (see `oqtoctl login` and `oqtoctl profiles`)
+    #[arg(long, short = 'p', env = "OQTO_PROFILE")]
+    profile: Option<String>,
 
     /// Output machine-readable JSON
     #[arg(long, global = true)]
@@ -227,6 +281,74 @@ enum Command {
         command: UiCommand,
     },
 
+    /// Manage background processes (dev servers, watchers) for agents
+    #[command(name = "bg")]
+    Bg {
+        #[command(subcommand)]
+        command: BgCommand,
+    },
+
+    /// Suggest memories for the user to review (for agents)
+    Memory {
+        #[command(subcommand)]
+        command: MemoryCommand,
+    },
+
+    /// Stage emails and Slack messages for the user's approval (for agents)
+    Outbox {
+        #[command(subcommand)]
+        command: OutboxCommand,
+    },
+
+    /// Manage agent schedules (prompts run in fresh sessions on a cron schedule)
+    Schedules {
+        #[command(subcommand)]
+        command: SchedulesCommand,
+    },
+
+    /// Manage personal access tokens for scripts and CI
+    Tokens {
+        #[command(subcommand)]
+        command: TokensCommand,
+    },
+
+    /// Sign in to a server and save the login as a profile
+    ///
+    /// Signs in with a password or, with --sso, through the server's single
+    /// sign-on, then creates a personal access token for this machine and
+    /// stores it in the OS keychain. The profile (--profile, else "default")
+    /// becomes the current one, so later commands go to that server.
+    ///
+    /// Example: oqtoctl --profile work login https://oqto.example.com --sso
+    Login {
+        /// Server URL, e.g. https://oqto.example.com
+        url: String,
+        /// Sign in through single sign-on by entering a code in a browser
+        #[arg(long, conflicts_with_all = ["username", "password_stdin"])]
+        sso: bool,
+        /// Username or email (prompted if not given)
+        #[arg(long, short = 'u')]
+        username: Option<String>,
+        /// Read the password from stdin instead of prompting
+        #[arg(long)]
+        password_stdin: bool,
+        /// Save an existing token read from stdin instead of signing in
+        #[arg(long, conflicts_with_all = ["sso", "username", "password_stdin"])]
+        token_stdin: bool,
+        /// Days until the created token expires
+        #[arg(long, default_value_t = 90)]
+        expires_in_days: i64,
+    },
+
+    /// Revoke the profile's token and forget the profile
+    Logout,
+
+    /// List saved profiles or switch the current one
+    Profiles {
+        #[command(subcommand)]
+        command: ProfilesCommand,
+    },
+
     /// Event bus commands (admin)
     #[command(name = "bus")]
     Bus {
@@ -431,6 +553,15 @@ enum UserCommand {
         /// New display name
         name: String,
     },
+    /// Set a user's disk quota in GiB (0 removes the limit)
+    SetQuota {
+        /// Username or user ID
+        user: String,
+        /// Quota in GiB
+        gb: u64,
+    },
+    /// Show per-user disk usage and quotas, largest first
+    DiskUsage,
     /// Re-provision eavs key + models.json + runner for a user
     Reprovision {
         /// Username or user ID
@@ -442,6 +573,35 @@ enum UserCommand {
         #[arg(long)]
         user: Option<String>,
     },
+    /// Export a user's environment (settings, macros, workspaces and their
+    /// memories) to a zip archive, e.g. to move to another server
+    ExportEnv {
+        /// Username or user ID
+        user: String,
+        /// Archive to write (default: oqto-env-<user>.zip)
+        #[arg(long, short)]
+        output: Option<PathBuf>,
+        /// Also include the workspace files
+        #[arg(long)]
+        include_data: bool,
+    },
+    /// Import an environment archive written by export-env into a user
+    ImportEnv {
+        /// Username or user ID
+        user: String,
+        /// Archive to import
+        archive: PathBuf,
+        /// What to do with settings, macros and workspaces that already
+        /// exist: skip, overwrite or rename
+        #[arg(long, default_value = "skip")]
+        on_conflict: String,
+        /// Leave workspace files in the archive out
+        #[arg(long)]
+        skip_data: bool,
+        /// Show what would change without changing anything
+        #[arg(long)]
+        dry_run: bool,
+    },
     /// Audit and remediate identity contract consistency for multi-user rollout.
     DoctorIdentity {
         /// Optional username or user ID to scope the check.
@@ -818,6 +978,258 @@ enum UiCommand {
     },
 }
 
+#[derive(Debug, Subcommand)]
+enum BgCommand {
+    /// Start a background process that outlives the current turn
+    ///
+    /// Example: oqtoctl bg start --name dev --restart -- npm run dev
+    Start {
+        /// Session ID (defaults to OQTO_SESSION_ID env var)
+        #[arg(long, short, env = "OQTO_SESSION_ID")]
+        session: String,
+        /// Display name (defaults to the program name)
+        #[arg(long, short)]
+        name: Option<String>,
+        /// Working directory inside the session's workspace (defaults to the current directory)
+        #[arg(long)]
+        cwd: Option<PathBuf>,
+        /// Restart the process when it crashes
+        #[arg(long)]
+        restart: bool,
+        /// Program and arguments
+        #[arg(required = true, last = true)]
+        command: Vec<String>,
+    },
+    /// List the session's background processes
+    List {
+        /// Session ID (defaults to OQTO_SESSION_ID env var)
+        #[arg(long, short, env = "OQTO_SESSION_ID")]
+        session: String,
+    },
+    /// Show recent output of a background process
+    Logs {
+        /// Session ID (defaults to OQTO_SESSION_ID env var)
+        #[arg(long, short, env = "OQTO_SESSION_ID")]
+        session: String,
+        /// Background process ID
+        id: String,
+        /// Number of lines
+        #[arg(long, short = 'n', default_value = "200")]
+        lines: usize,
+    },
+    /// Stop a background process
+    Stop {
+        /// Session ID (defaults to OQTO_SESSION_ID env var)
+        #[arg(long, short, env = "OQTO_SESSION_ID")]
+        session: String,
+        /// Background process ID
+        id: String,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+enum MemoryCommand {
+    /// Suggest a finding for the user's long-term memory
+    ///
+    /// Nothing is stored until the user accepts the suggestion.
+    /// Example: oqtoctl memory suggest "CI needs cargo --locked" --tag ci
+    Suggest {
+        /// Session ID (defaults to OQTO_SESSION_ID env var)
+        #[arg(long, short, env = "OQTO_SESSION_ID")]
+        session: String,
+        /// The memory text
+        content: String,
+        /// Tags (repeatable)
+        #[arg(long = "tag", short)]
+        tags: Vec<String>,
+        /// Category (e.g. "ops", "preferences")
+        #[arg(long)]
+        category: Option<String>,
+        /// Importance from 1 to 10
+        #[arg(long)]
+        importance: Option<i32>,
+        /// Message the finding came from
+        #[arg(long)]
+        message: Option<String>,
+        /// Why this is worth remembering (shown to the user)
+        #[arg(long)]
+        reason: Option<String>,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+enum OutboxCommand {
+    /// Stage an email; it is sent only after the user approves it
+    ///
+    /// Example: oqtoctl outbox email --to bob@example.com --subject "v1.2" "Released today."
+    Email {
+        /// Session ID (defaults to OQTO_SESSION_ID env var)
+        #[arg(long, short, env = "OQTO_SESSION_ID")]
+        session: String,
+        /// Recipient address (repeatable)
+        #[arg(long, required = true)]
+        to: Vec<String>,
+        /// Cc address (repeatable)
+        #[arg(long)]
+        cc: Vec<String>,
+        /// Subject line
+        #[arg(long)]
+        subject: String,
+        /// Message text (reads from stdin if not provided)
+        body: Option<String>,
+        /// Why the message should be sent (shown to the user)
+        #[arg(long)]
+        reason: Option<String>,
+    },
+    /// Stage a Slack message; it is posted only after the user approves it
+    Slack {
+        /// Session ID (defaults to OQTO_SESSION_ID env var)
+        #[arg(long, short, env = "OQTO_SESSION_ID")]
+        session: String,
+        /// Channel name or ID
+        #[arg(long)]
+        channel: String,
+        /// Message text (reads from stdin if not provided)
+        body: Option<String>,
+        /// Why the message should be sent (shown to the user)
+        #[arg(long)]
+        reason: Option<String>,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+enum SchedulesCommand {
+    /// List your agent schedules
+    List,
+    /// Show one agent schedule
+    Show {
+        /// Schedule name
+        name: String,
+    },
+    /// Create an agent schedule
+    ///
+    /// Example: oqtoctl schedules create deps --cron "0 3 * * *" \
+    ///   --workspace ~/oqto/projects/api "Update dependencies and open a PR"
+    Create {
+        /// Schedule name (letters, digits, '-' and '_')
+        name: String,
+        /// Five-field cron expression (UTC)
+        #[arg(long)]
+        cron: String,
+        /// Workspace the session runs in
+        #[arg(long)]
+        workspace: String,
+        /// Prompt sent to the agent (arguments of --template if given)
+        prompt: String,
+        /// Run this prompt template (`/<template> <prompt>`) instead
+        #[arg(long)]
+        template: Option<String>,
+        /// Agent harness (defaults to pi)
+        #[arg(long)]
+        harness: Option<String>,
+        /// Model provider
+        #[arg(long)]
+        provider: Option<String>,
+        /// Model ID
+        #[arg(long)]
+        model: Option<String>,
+        /// Stop and fail runs still working after this many seconds
+        #[arg(long)]
+        timeout: Option<i64>,
+        /// Create the schedule disabled
+        #[arg(long)]
+        disabled: bool,
+    },
+    /// Change an agent schedule; unset options keep their value
+    Update {
+        /// Schedule name
+        name: String,
+        #[arg(long)]
+        cron: Option<String>,
+        #[arg(long)]
+        workspace: Option<String>,
+        #[arg(long)]
+        prompt: Option<String>,
+        #[arg(long)]
+        template: Option<String>,
+        #[arg(long)]
+        harness: Option<String>,
+        #[arg(long)]
+        provider: Option<String>,
+        #[arg(long)]
+        model: Option<String>,
+        #[arg(long)]
+        timeout: Option<i64>,
+        /// Enable or disable the schedule
+        #[arg(long)]
+        enabled: Option<bool>,
+    },
+    /// Delete an agent schedule (its run history is kept)
+    Delete {
+        /// Schedule name
+        name: String,
+    },
+    /// Run an agent schedule now
+    Run {
+        /// Schedule name
+        name: String,
+    },
+    /// Show a schedule's run history, newest first
+    Runs {
+        /// Schedule name
+        name: String,
+        /// Only runs with this status (running, succeeded, failed, skipped)
+        #[arg(long)]
+        status: Option<String>,
+        /// Maximum runs to show
+        #[arg(long, default_value = "20")]
+        limit: i64,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+enum TokensCommand {
+    /// List your tokens, including revoked and expired ones
+    List,
+    /// Create a token; it is shown only once
+    ///
+    /// Example: oqtoctl tokens create ci --scope read --expires-in-days 90
+    ///
+    /// Send it as `Authorization: Bearer <token>` or set OQTO_AUTH_TOKEN.
+    Create {
+        /// Name to recognize the token by
+        name: String,
+        /// Scope to grant: read, write (implies read) or admin; repeatable.
+        /// Defaults to read and write
+        #[arg(long = "scope")]
+        scopes: Vec<String>,
+        /// Expire the token after this many days (default: never)
+        #[arg(long)]
+        expires_in_days: Option<i64>,
+    },
+    /// Show one token
+    Show {
+        /// Token ID
+        id: String,
+    },
+    /// Revoke a token; requests using it fail from then on
+    Revoke {
+        /// Token ID
+        id: String,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+enum ProfilesCommand {
+    /// List saved profiles; the current one is marked with *
+    List,
+    /// Make a profile the current one
+    Use {
+        /// Profile name
+        name: String,
+    },
+}
+
 #[cfg(unix)]
 type UnixClient = HyperClient<UnixConnector, Full<Bytes>>;
 
@@ -863,9 +1275,8 @@ struct OqtoClient {
 }
 
 impl OqtoClient {
-    fn new(base_url: &str, admin_socket: Option<&str>) -> Result<Self> {
+    fn new(base_url: &str, admin_socket: Option<&str>, auth_token: Option<String>) -> Result<Self> {
         let base_url = base_url.trim_end_matches('/').to_string();
-        let auth_token = std::env::var("OQTO_AUTH_TOKEN").ok();
         let dev_user = std::env::var("OQTO_DEV_USER").ok();
 
         #[cfg(unix)]
@@ -911,6 +1322,19 @@ impl OqtoClient {
         })
     }
 
+    /// A client that always talks HTTP to `base_url`, for signing in to a
+    /// server that may be this host.
+    fn http(base_url: &str, auth_token: Option<String>) -> Self {
+        Self {
+            transport: OqtoTransport::Http {
+                base_url: base_url.trim_end_matches('/').to_string(),
+                client: reqwest::Client::new(),
+            },
+            dev_user: None,
+            auth_token,
+        }
+    }
+
     fn display_url(&self) -> String {
         match &self.transport {
             OqtoTransport::Http { base_url, .. } => base_url.clone(),
@@ -1048,12 +1472,59 @@ impl OqtoClient {
         }
     }
 
+    /// POST a raw body (e.g. an archive) with the given content type.
+    async fn post_bytes(
+        &self,
+        path: &str,
+        content_type: &str,
+        body: Vec<u8>,
+    ) -> Result<OqtoResponse> {
+        match &self.transport {
+            OqtoTransport::Http { base_url, client } => {
+                let url = format!("{}{}", base_url, path);
+                let response = self
+                    .with_auth_headers(
+                        client
+                            .post(&url)
+                            .header(reqwest::header::CONTENT_TYPE, content_type)
+                            .body(body),
+                    )
+                    .send()
+                    .await
+                    .context("sending request to server")?;
+                response_to_oqto(response).await
+            }
+            #[cfg(unix)]
+            OqtoTransport::Unix { .. } => {
+                self.request_unix_with_content_type(
+                    hyper::Method::POST,
+                    path,
+                    Some(body),
+                    content_type,
+                )
+                .await
+            }
+        }
+    }
+
     #[cfg(unix)]
     async fn request_unix(
         &self,
         method: hyper::Method,
         path: &str,
         body: Option<Vec<u8>>,
+    ) -> Result<OqtoResponse> {
+        self.request_unix_with_content_type(method, path, body, "application/json")
+            .await
+    }
+
+    #[cfg(unix)]
+    async fn request_unix_with_content_type(
+        &self,
+        method: hyper::Method,
+        path: &str,
+        body: Option<Vec<u8>>,
+        content_type: &str,
     ) -> Result<OqtoResponse> {
         let (socket_path, base_path, client) = match &self.transport {
             OqtoTransport::Unix {
@@ -1070,7 +1541,7 @@ impl OqtoClient {
         let body = body.unwrap_or_default();
         let mut builder = hyper::Request::builder().method(method).uri(uri);
         if !body.is_empty() {
-            builder = builder.header("content-type", "application/json");
+            builder = builder.header("content-type", content_type);
         }
         let request = builder
             .body(Full::new(Bytes::from(body)))
@@ -2559,6 +3030,73 @@ async fn handle_user(client: &OqtoClient, command: UserCommand, json: bool) -> R
             }
         }
 
+        UserCommand::SetQuota { user, gb } => {
+            let user_id = resolve_user_id(client, &user, json).await?;
+            let body = serde_json::json!({ "gb": gb });
+            let response = client
+                .put_json(&format!("/admin/users/{}/quota", user_id), &body)
+                .await?;
+            let status = response.status();
+
+            if status.is_success() {
+                if json {
+                    println!(
+                        "{}",
+                        serde_json::json!({"status": "updated", "user": user_id, "gb": gb})
+                    );
+                } else if gb == 0 {
+                    println!("Disk quota removed for '{}'.", user);
+                } else {
+                    println!("Disk quota set to {} GiB for '{}'.", gb, user);
+                }
+            } else {
+                let body_text = response.text().await.unwrap_or_default();
+                anyhow::bail!("Failed to set quota (HTTP {status}): {body_text}");
+            }
+        }
+
+        UserCommand::DiskUsage => {
+            let response = client.get("/admin/disk-usage").await?;
+            let status = response.status();
+            if !status.is_success() {
+                let body_text = response.text().await.unwrap_or_default();
+                anyhow::bail!("Failed to read disk usage (HTTP {status}): {body_text}");
+            }
+
+            let payload: serde_json::Value = response.json().await?;
+            if json {
+                println!("{}", serde_json::to_string_pretty(&payload)?);
+            } else {
+                let users = payload["users"].as_array().cloned().unwrap_or_default();
+                if users.is_empty() {
+                    println!("No quota data reported.");
+                } else {
+                    let gib = |bytes: u64| bytes as f64 / (1u64 << 30) as f64;
+                    println!(
+                        "{:<24} {:<24} {:>10} {:>10}",
+                        "USER", "LINUX USER", "USED GiB", "QUOTA GiB"
+                    );
+                    println!("{}", "-".repeat(71));
+                    for entry in users {
+                        let used = entry["used_bytes"].as_u64().unwrap_or(0);
+                        let hard = entry["hard_limit_bytes"].as_u64().unwrap_or(0);
+                        let quota = if hard == 0 {
+                            "-".to_string()
+                        } else {
+                            format!("{:.1}", gib(hard))
+                        };
+                        println!(
+                            "{:<24} {:<24} {:>10.1} {:>10}",
+                            entry["username"].as_str().unwrap_or("-"),
+                            entry["linux_username"].as_str().unwrap_or("?"),
+                            gib(used),
+                            quota
+                        );
+                    }
+                }
+            }
+        }
+
         UserCommand::Reprovision { user } => {
             let user_id = resolve_user_id(client, &user, json).await?;
 
@@ -2619,9 +3157,87 @@ async fn handle_user(client: &OqtoClient, command: UserCommand, json: bool) -> R
             }
         }
 
-        UserCommand::DoctorIdentity { user, apply } => {
-            doctor_identity(user.as_deref(), apply, json).await?;
-        }
+        UserCommand::ExportEnv {
+            user,
+            output,
+            include_data,
+        } => {
+            let user_id = resolve_user_id(client, &user, json).await?;
+            let response = client
+                .get(&format!(
+                    "/admin/users/{}/environment/export?include_data={}",
+                    user_id, include_data
+                ))
+                .await?;
+            let status = response.status();
+            if !status.is_success() {
+                let body_text = response.text().await.unwrap_or_default();
+                anyhow::bail!("Failed to export environment (HTTP {status}): {body_text}");
+            }
+
+            let output = output.unwrap_or_else(|| PathBuf::from(format!("oqto-env-{user}.zip")));
+            std::fs::write(&output, &response.body)
+                .with_context(|| format!("writing {}", output.display()))?;
+            if json {
+                println!(
+                    "{}",
+                    serde_json::json!({
+                        "status": "exported",
+                        "user": user_id,
+                        "path": output,
+                        "bytes": response.body.len(),
+                    })
+                );
+            } else {
+                println!(
+                    "Exported environment of '{}' to {} ({} bytes).",
+                    user,
+                    output.display(),
+                    response.body.len()
+                );
+            }
+        }
+
+        UserCommand::ImportEnv {
+            user,
+            archive,
+            on_conflict,
+            skip_data,
+            dry_run,
+        } => {
+            if !matches!(on_conflict.as_str(), "skip" | "overwrite" | "rename") {
+                anyhow::bail!("--on-conflict must be skip, overwrite or rename");
+            }
+            let user_id = resolve_user_id(client, &user, json).await?;
+            let body = std::fs::read(&archive)
+                .with_context(|| format!("reading {}", archive.display()))?;
+            let response = client
+                .post_bytes(
+                    &format!(
+                        "/admin/users/{}/environment/import?on_conflict={}&skip_data={}&dry_run={}",
+                        user_id, on_conflict, skip_data, dry_run
+                    ),
+                    "application/zip",
+                    body,
+                )
+                .await?;
+            let status = response.status();
+            if !status.is_success() {
+                let body_text = response.text().await.unwrap_or_default();
+                anyhow::bail!("Failed to import environment (HTTP {status}): {body_text}");
+            }
+
+            let report: serde_json::Value = response.json().await?;
+            if json {
+                println!("{}", serde_json::to_string_pretty(&report)?);
+            } else {
+                print_import_report(&report);
+            }
+        }
+
+        UserCommand::DoctorIdentity { user, apply } => {
+            doctor_identity(user.as_deref(), apply, json).await?;
+        }
 
         UserCommand::Delete { user, force } => {
             // Resolve user to get details (try API first, fall back to system lookup)
@@ -2995,6 +3611,64 @@ fn read_password_no_echo() -> Result<String> {
 ///
 /// Tries the input as a user ID first (GET /admin/users/{input}). If that
 /// fails with 404, lists all users and searches for a matching username.
+/// Print the report of an environment import.
+fn print_import_report(report: &serde_json::Value) {
+    let source = &report["source"];
+    println!(
+        "{} environment of '{}' (exported {}):",
+        if report["dry_run"].as_bool().unwrap_or(false) {
+            "Would import"
+        } else {
+            "Imported"
+        },
+        source["username"].as_str().unwrap_or("?"),
+        report["exported_at"].as_str().unwrap_or("?")
+    );
+
+    let settings = &report["settings"];
+    let keys = |name: &str| {
+        settings[name]
+            .as_array()
+            .map(|keys| {
+                keys.iter()
+                    .filter_map(|k| k.as_str())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            })
+            .filter(|keys| !keys.is_empty())
+            .unwrap_or_else(|| "-".to_string())
+    };
+    println!("  Settings");
+    println!("    added:    {}", keys("added"));
+    println!("    replaced: {}", keys("replaced"));
+    println!("    kept:     {}", keys("kept"));
+
+    for (title, field) in [("Macros", "macros"), ("Workspaces", "workspaces")] {
+        let items = report[field].as_array().cloned().unwrap_or_default();
+        println!("  {} ({})", title, items.len());
+        for item in items {
+            let mut line = format!(
+                "    {:<9} {}",
+                item["action"].as_str().unwrap_or("?"),
+                item["name"].as_str().unwrap_or("?")
+            );
+            if let Some(new_name) = item["imported_as"].as_str() {
+                line.push_str(&format!(" -> {new_name}"));
+            }
+            if let Some(files) = item["files"].as_u64() {
+                line.push_str(&format!(", {files} files"));
+            }
+            if let Some(memories) = item["memories"].as_u64() {
+                line.push_str(&format!(", {memories} memories"));
+            }
+            if let Some(error) = item["error"].as_str() {
+                line.push_str(&format!(": {error}"));
+            }
+            println!("{line}");
+        }
+    }
+}
+
 async fn resolve_user_id(client: &OqtoClient, user_or_id: &str, _json: bool) -> Result<String> {
     // Try as user ID first
     let response = client.get(&format!("/admin/users/{}", user_or_id)).await?;
@@ -5456,6 +6130,934 @@ async fn handle_bus(client: &OqtoClient, command: BusCommand, json: bool) -> Res
     }
 }
 
+async fn handle_bg(client: &OqtoClient, command: BgCommand, json: bool) -> Result<()> {
+    let (response, session) = match command {
+        BgCommand::Start {
+            session,
+            name,
+            cwd,
+            restart,
+            command,
+        } => {
+            let cwd = match cwd {
+                Some(cwd) => Some(cwd),
+                None => std::env::current_dir().ok(),
+            };
+            let body = serde_json::json!({
+                "command": command,
+                "name": name,
+                "cwd": cwd,
+                "restart": if restart { "on_failure" } else { "never" },
+            });
+            let path = format!("/sessions/{}/processes", urlencoding::encode(&session));
+            (client.post_json(&path, &body).await?, session)
+        }
+        BgCommand::List { session } => {
+            let path = format!("/sessions/{}/processes", urlencoding::encode(&session));
+            (client.get(&path).await?, session)
+        }
+        BgCommand::Logs { session, id, lines } => {
+            let path = format!(
+                "/sessions/{}/processes/{}/logs?lines={}",
+                urlencoding::encode(&session),
+                urlencoding::encode(&id),
+                lines
+            );
+            (client.get(&path).await?, session)
+        }
+        BgCommand::Stop { session, id } => {
+            let path = format!(
+                "/sessions/{}/processes/{}",
+                urlencoding::encode(&session),
+                urlencoding::encode(&id)
+            );
+            (client.delete(&path).await?, session)
+        }
+    };
+
+    if !response.status().is_success() {
+        let status = response.status();
+        let body = response.text().await.unwrap_or_default();
+        anyhow::bail!(
+            "Request for session {} failed ({}): {}",
+            session,
+            status,
+            body
+        );
+    }
+    let result: serde_json::Value = response.json().await?;
+    if json {
+        println!("{}", serde_json::to_string(&result)?);
+        return Ok(());
+    }
+
+    let print_process = |p: &serde_json::Value| {
+        let command = p["command"]
+            .as_array()
+            .map(|parts| {
+                parts
+                    .iter()
+                    .filter_map(|v| v.as_str())
+                    .collect::<Vec<_>>()
+                    .join(" ")
+            })
+            .unwrap_or_default();
+        println!(
+            "{:<16} {:<16} {:<10} {:>8}  {}",
+            p["id"].as_str().unwrap_or("?"),
+            p["name"].as_str().unwrap_or("?"),
+            p["status"].as_str().unwrap_or("?"),
+            p["pid"]
+                .as_u64()
+                .map(|pid| pid.to_string())
+                .unwrap_or_default(),
+            command
+        );
+    };
+    if let Some(lines) = result["lines"].as_array() {
+        for line in lines {
+            println!("{}", line.as_str().unwrap_or_default());
+        }
+    } else if let Some(processes) = result.as_array() {
+        if processes.is_empty() {
+            println!("No background processes");
+        } else {
+            println!(
+                "{:<16} {:<16} {:<10} {:>8}  COMMAND",
+                "ID", "NAME", "STATUS", "PID"
+            );
+            processes.iter().for_each(print_process);
+        }
+    } else {
+        print_process(&result);
+    }
+    Ok(())
+}
+
+async fn handle_memory(client: &OqtoClient, command: MemoryCommand, json: bool) -> Result<()> {
+    let MemoryCommand::Suggest {
+        session,
+        content,
+        tags,
+        category,
+        importance,
+        message,
+        reason,
+    } = command;
+    let body = serde_json::json!({
+        "content": content,
+        "tags": tags,
+        "category": category,
+        "importance": importance,
+        "message_id": message,
+        "reason": reason,
+    });
+    let path = format!(
+        "/sessions/{}/memory-suggestions",
+        urlencoding::encode(&session)
+    );
+    let response = client.post_json(&path, &body).await?;
+    if !response.status().is_success() {
+        let status = response.status();
+        let body = response.text().await.unwrap_or_default();
+        anyhow::bail!(
+            "Memory suggestion for session {} failed ({}): {}",
+            session,
+            status,
+            body
+        );
+    }
+    let result: serde_json::Value = response.json().await?;
+    if json {
+        println!("{}", serde_json::to_string(&result)?);
+    } else {
+        println!(
+            "Suggested memory {} (waiting for the user's review)",
+            result["id"].as_str().unwrap_or("?")
+        );
+    }
+    Ok(())
+}
+
+async fn handle_outbox(client: &OqtoClient, command: OutboxCommand, json: bool) -> Result<()> {
+    let (session, channel, recipients, cc, subject, body, reason) = match command {
+        OutboxCommand::Email {
+            session,
+            to,
+            cc,
+            subject,
+            body,
+            reason,
+        } => (session, "email", to, cc, Some(subject), body, reason),
+        OutboxCommand::Slack {
+            session,
+            channel,
+            body,
+            reason,
+        } => (
+            session,
+            "slack",
+            vec![channel],
+            Vec::new(),
+            None,
+            body,
+            reason,
+        ),
+    };
+    let body = match body {
+        Some(body) => body,
+        None => {
+            let mut input = String::new();
+            io::stdin()
+                .read_to_string(&mut input)
+                .context("reading from stdin")?;
+            input
+        }
+    };
+    let request = serde_json::json!({
+        "channel": channel,
+        "recipients": recipients,
+        "cc": cc,
+        "subject": subject,
+        "body": body,
+        "reason": reason,
+    });
+    let path = format!("/sessions/{}/outbox", urlencoding::encode(&session));
+    let response = client.post_json(&path, &request).await?;
+    if !response.status().is_success() {
+        let status = response.status();
+        let body = response.text().await.unwrap_or_default();
+        anyhow::bail!(
+            "Staging {} message for session {} failed ({}): {}",
+            channel,
+            session,
+            status,
+            body
+        );
+    }
+    let result: serde_json::Value = response.json().await?;
+    if json {
+        println!("{}", serde_json::to_string(&result)?);
+    } else {
+        println!(
+            "Staged {} message {} (not sent until the user approves it)",
+            channel,
+            result["id"].as_str().unwrap_or("?")
+        );
+    }
+    Ok(())
+}
+
+async fn handle_schedules(
+    client: &OqtoClient,
+    command: SchedulesCommand,
+    json: bool,
+) -> Result<()> {
+    match command {
+        SchedulesCommand::List => {
+            let schedules = schedules_request(client.get("/schedules").await?, "Listing").await?;
+            if json {
+                println!("{}", serde_json::to_string(&schedules)?);
+                return Ok(());
+            }
+            let schedules = schedules.as_array().cloned().unwrap_or_default();
+            if schedules.is_empty() {
+                println!("No agent schedules");
+            }
+            for schedule in schedules {
+                print_schedule_line(&schedule);
+            }
+        }
+        SchedulesCommand::Show { name } => {
+            let path = format!("/schedules/{}", urlencoding::encode(&name));
+            let schedule = schedules_request(client.get(&path).await?, "Loading").await?;
+            if json {
+                println!("{}", serde_json::to_string(&schedule)?);
+            } else {
+                println!("{}", serde_json::to_string_pretty(&schedule)?);
+            }
+        }
+        SchedulesCommand::Create {
+            name,
+            cron,
+            workspace,
+            prompt,
+            template,
+            harness,
+            provider,
+            model,
+            timeout,
+            disabled,
+        } => {
+            let body = serde_json::json!({
+                "name": name,
+                "cron": cron,
+                "workspace_path": workspace,
+                "prompt": prompt,
+                "template": template,
+                "harness": harness,
+                "provider": provider,
+                "model": model,
+                "timeout_secs": timeout,
+                "enabled": !disabled,
+            });
+            let schedule =
+                schedules_request(client.post_json("/schedules", &body).await?, "Creating").await?;
+            if json {
+                println!("{}", serde_json::to_string(&schedule)?);
+            } else {
+                print_schedule_line(&schedule);
+            }
+        }
+        SchedulesCommand::Update {
+            name,
+            cron,
+            workspace,
+            prompt,
+            template,
+            harness,
+            provider,
+            model,
+            timeout,
+            enabled,
+        } => {
+            let path = format!("/schedules/{}", urlencoding::encode(&name));
+            let mut body = schedules_request(client.get(&path).await?, "Loading").await?;
+            let changes = [
+                ("cron", cron.map(serde_json::Value::from)),
+                ("workspace_path", workspace.map(serde_json::Value::from)),
+                ("prompt", prompt.map(serde_json::Value::from)),
+                ("template", template.map(serde_json::Value::from)),
+                ("harness", harness.map(serde_json::Value::from)),
+                ("provider", provider.map(serde_json::Value::from)),
+                ("model", model.map(serde_json::Value::from)),
+                ("timeout_secs", timeout.map(serde_json::Value::from)),
+                ("enabled", enabled.map(serde_json::Value::from)),
+            ];
+            for (key, value) in changes {
+                if let Some(value) = value {
+                    body[key] = value;
+                }
+            }
+            let schedule =
+                schedules_request(client.put_json(&path, &body).await?, "Updating").await?;
+            if json {
+                println!("{}", serde_json::to_string(&schedule)?);
+            } else {
+                print_schedule_line(&schedule);
+            }
+        }
+        SchedulesCommand::Delete { name } => {
+            let path = format!("/schedules/{}", urlencoding::encode(&name));
+            let response = client.delete(&path).await?;
+            if !response.status().is_success() {
+                let status = response.status();
+                let body = response.text().await.unwrap_or_default();
+                anyhow::bail!("Deleting schedule {} failed ({}): {}", name, status, body);
+            }
+            if json {
+                println!("{}", serde_json::json!({ "deleted": name }));
+            } else {
+                println!("Deleted schedule {}", name);
+            }
+        }
+        SchedulesCommand::Run { name } => {
+            let path = format!("/schedules/{}/run", urlencoding::encode(&name));
+            let run = schedules_request(client.post(&path).await?, "Starting").await?;
+            if json {
+                println!("{}", serde_json::to_string(&run)?);
+            } else {
+                println!(
+                    "Started run {} of {} in session {}",
+                    run["id"].as_str().unwrap_or("?"),
+                    name,
+                    run["session_id"].as_str().unwrap_or("?")
+                );
+            }
+        }
+        SchedulesCommand::Runs {
+            name,
+            status,
+            limit,
+        } => {
+            let mut path = format!(
+                "/schedules/{}/runs?limit={}",
+                urlencoding::encode(&name),
+                limit
+            );
+            if let Some(status) = status {
+                path.push_str(&format!("&status={}", urlencoding::encode(&status)));
+            }
+            let page = schedules_request(client.get(&path).await?, "Loading runs of").await?;
+            if json {
+                println!("{}", serde_json::to_string(&page)?);
+                return Ok(());
+            }
+            for run in page["runs"].as_array().cloned().unwrap_or_default() {
+                println!(
+                    "{:<26} {:<10} {}",
+                    run["run_at"].as_str().unwrap_or("-"),
+                    run["status"].as_str().unwrap_or("?"),
+                    run["error"].as_str().unwrap_or("")
+                );
+            }
+            println!(
+                "{} runs, failure streak {}",
+                page["total"].as_i64().unwrap_or(0),
+                page["failure_streak"].as_i64().unwrap_or(0)
+            );
+        }
+    }
+    Ok(())
+}
+
+async fn handle_tokens(client: &OqtoClient, command: TokensCommand, json: bool) -> Result<()> {
+    // The admin socket is not a user account tokens could belong to.
+    if client.is_admin_socket() {
+        anyhow::bail!(
+            "Tokens belong to a user; run oqtoctl login or set OQTO_AUTH_TOKEN to act as one"
+        );
+    }
+    match command {
+        TokensCommand::List => {
+            let page = tokens_request(client.get("/tokens").await?, "Listing").await?;
+            if json {
+                println!("{}", serde_json::to_string(&page)?);
+                return Ok(());
+            }
+            let tokens = page["keys"].as_array().cloned().unwrap_or_default();
+            if tokens.is_empty() {
+                println!("No tokens");
+            }
+            for token in tokens {
+                print_token_line(&token);
+            }
+        }
+        TokensCommand::Create {
+            name,
+            scopes,
+            expires_in_days,
+        } => {
+            if expires_in_days.is_some_and(|days| days <= 0) {
+                anyhow::bail!("--expires-in-days must be positive");
+            }
+            let expires_at = expires_in_days
+                .map(|days| (chrono::Utc::now() + chrono::Duration::days(days)).to_rfc3339());
+            let body = serde_json::json!({
+                "name": name,
+                "scopes": (!scopes.is_empty()).then_some(scopes),
+                "expires_at": expires_at,
+            });
+            let created =
+                tokens_request(client.post_json("/tokens", &body).await?, "Creating").await?;
+            if json {
+                println!("{}", serde_json::to_string(&created)?);
+            } else {
+                print_token_line(&created);
+                println!();
+                println!("{}", created["api_key"].as_str().unwrap_or("?"));
+                println!();
+                println!("Store it now; it cannot be shown again.");
+            }
+        }
+        TokensCommand::Show { id } => {
+            let path = format!("/tokens/{}", urlencoding::encode(&id));
+            let token = tokens_request(client.get(&path).await?, "Loading").await?;
+            if json {
+                println!("{}", serde_json::to_string(&token)?);
+            } else {
+                println!("{}", serde_json::to_string_pretty(&token)?);
+            }
+        }
+        TokensCommand::Revoke { id } => {
+            let path = format!("/tokens/{}", urlencoding::encode(&id));
+            let response = client.delete(&path).await?;
+            if !response.status().is_success() {
+                let status = response.status();
+                let body = response.text().await.unwrap_or_default();
+                anyhow::bail!("Revoking token {} failed ({}): {}", id, status, body);
+            }
+            if json {
+                println!("{}", serde_json::json!({ "revoked": id }));
+            } else {
+                println!("Revoked token {}", id);
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Fail with the server's message unless the request succeeded, otherwise
+/// return the response body.
+async fn tokens_request(response: OqtoResponse, action: &str) -> Result<serde_json::Value> {
+    if !response.status().is_success() {
+        let status = response.status();
+        let body = response.text().await.unwrap_or_default();
+        anyhow::bail!("{} token failed ({}): {}", action, status, body);
+    }
+    Ok(response.json().await?)
+}
+
+fn print_token_line(token: &serde_json::Value) {
+    let state = if token["revoked_at"].is_string() {
+        "revoked".to_string()
+    } else {
+        match token["expires_at"].as_str() {
+            Some(expires_at) => format!("expires {expires_at}"),
+            None => "no expiry".to_string(),
+        }
+    };
+    let scopes: Vec<&str> = token["scopes"]
+        .as_array()
+        .map(|scopes| scopes.iter().filter_map(|s| s.as_str()).collect())
+        .unwrap_or_default();
+    println!(
+        "{:<38} {:<20} {:<18} {}",
+        token["id"].as_str().unwrap_or("?"),
+        token["name"].as_str().unwrap_or("?"),
+        if scopes.is_empty() {
+            "all".to_string()
+        } else {
+            scopes.join(",")
+        },
+        state
+    );
+}
+
+/// Server and token for all commands but `login`, `logout` and
+/// `profiles`. `--server` and OQTO_AUTH_TOKEN win over the profile; a
+/// profile is used when named with --profile, or when it is the current
+/// one and neither --server nor --admin-socket is given. Without one,
+/// commands go to the local server.
+fn resolve_target(
+    server: Option<&str>,
+    profile: Option<&str>,
+    admin_socket: bool,
+) -> Result<(String, Option<String>)> {
+    let env_token = std::env::var("OQTO_AUTH_TOKEN").ok();
+    let store = if profile.is_some() || (server.is_none() && !admin_socket) {
+        ProfileStore::load()?
+    } else {
+        ProfileStore::default()
+    };
+    let Some((name, selected)) = store.selected(profile)? else {
+        let server = server.unwrap_or(DEFAULT_SERVER_URL).to_string();
+        return Ok((server, env_token));
+    };
+    let auth_token = match env_token {
+        Some(token) => token,
+        None => selected.load_token(&name)?,
+    };
+    let server = server.unwrap_or(&selected.server).to_string();
+    Ok((server, Some(auth_token)))
+}
+
+/// How `oqtoctl login` gets a session.
+enum LoginMethod {
+    Password {
+        username: Option<String>,
+        password_stdin: bool,
+    },
+    /// OIDC device authorization: the user enters a code in a browser.
+    Sso,
+    /// A token the user already has, read from stdin.
+    Token,
+}
+
+async fn handle_login(
+    url: &str,
+    profile: Option<&str>,
+    method: LoginMethod,
+    expires_in_days: i64,
+    json: bool,
+) -> Result<()> {
+    let name = profile.unwrap_or(profiles::DEFAULT_PROFILE);
+    profiles::validate_name(name)?;
+    if expires_in_days <= 0 {
+        anyhow::bail!("--expires-in-days must be positive");
+    }
+    let server = profiles::normalize_server_url(url)?;
+    let anonymous = OqtoClient::http(&server, None);
+
+    let login = match method {
+        LoginMethod::Token => {
+            let mut token = String::new();
+            io::stdin().read_to_string(&mut token)?;
+            let token = token.trim().to_string();
+            if token.is_empty() {
+                anyhow::bail!("No token on stdin");
+            }
+            let me = login_request(
+                OqtoClient::http(&server, Some(token.clone()))
+                    .get("/me")
+                    .await?,
+                "Checking the token",
+            )
+            .await?;
+            SavedLogin {
+                token,
+                token_id: None,
+                expires_at: None,
+                user: me["email"].as_str().map(str::to_string),
+            }
+        }
+        LoginMethod::Sso => {
+            let session = device_login(&anonymous).await?;
+            create_login_token(&server, name, &session, expires_in_days).await?
+        }
+        LoginMethod::Password {
+            username,
+            password_stdin,
+        } => {
+            let session = password_login(&anonymous, username, password_stdin).await?;
+            create_login_token(&server, name, &session, expires_in_days).await?
+        }
+    };
+    let SavedLogin {
+        token,
+        token_id,
+        expires_at,
+        user,
+    } = login;
+
+    let storage = if profiles::store_token(name, &token)? {
+        TokenStorage::Keychain
+    } else {
+        eprintln!(
+            "No OS keychain available; keeping the token in {} (readable only by you)",
+            ProfileStore::path()?.display()
+        );
+        TokenStorage::File
+    };
+    let mut store = ProfileStore::load()?;
+    let previous = store.profiles.insert(
+        name.to_string(),
+        Profile {
+            server: server.clone(),
+            user: user.clone(),
+            token_id,
+            expires_at,
+            storage,
+            token: (storage == TokenStorage::File).then(|| token.clone()),
+        },
+    );
+    store.current = Some(name.to_string());
+    store.save()?;
+
+    // The token this login replaces is of no use any more.
+    if let Some(previous) = previous
+        && let Some(id) = previous.token_id
+        && previous.server == server
+    {
+        let client = OqtoClient::http(&server, Some(token));
+        let _ = client
+            .delete(&format!("/tokens/{}", urlencoding::encode(&id)))
+            .await;
+    }
+
+    if json {
+        println!(
+            "{}",
+            serde_json::json!({
+                "profile": name,
+                "server": server,
+                "user": user,
+                "storage": storage,
+            })
+        );
+    } else {
+        println!(
+            "Logged in to {} as {} (profile {}, token in {})",
+            server,
+            user.as_deref().unwrap_or("?"),
+            name,
+            storage
+        );
+    }
+    Ok(())
+}
+
+/// The token a profile keeps, and what is known about it.
+struct SavedLogin {
+    token: String,
+    token_id: Option<String>,
+    expires_at: Option<String>,
+    user: Option<String>,
+}
+
+/// Trade a session from `/auth/login` for a personal access token for this
+/// machine, with the admin scope for admins.
+async fn create_login_token(
+    server: &str,
+    profile: &str,
+    session: &serde_json::Value,
+    expires_in_days: i64,
+) -> Result<SavedLogin> {
+    let session_token = session["token"]
+        .as_str()
+        .ok_or_else(|| anyhow!("Login response has no token"))?;
+    let user = &session["user"];
+    let mut scopes = vec!["read", "write"];
+    if user["role"].as_str() == Some("admin") {
+        scopes.push("admin");
+    }
+    let body = serde_json::json!({
+        "name": format!("oqtoctl {} on {}", profile, host_name()),
+        "scopes": scopes,
+        "expires_at": (chrono::Utc::now() + chrono::Duration::days(expires_in_days)).to_rfc3339(),
+    });
+    let created = tokens_request(
+        OqtoClient::http(server, Some(session_token.to_string()))
+            .post_json("/tokens", &body)
+            .await?,
+        "Creating",
+    )
+    .await?;
+    Ok(SavedLogin {
+        token: created["api_key"]
+            .as_str()
+            .ok_or_else(|| anyhow!("Token response has no api_key"))?
+            .to_string(),
+        token_id: created["id"].as_str().map(str::to_string),
+        expires_at: created["expires_at"].as_str().map(str::to_string),
+        user: user["email"].as_str().map(str::to_string),
+    })
+}
+
+async fn password_login(
+    client: &OqtoClient,
+    username: Option<String>,
+    password_stdin: bool,
+) -> Result<serde_json::Value> {
+    let username = match username {
+        Some(username) => username,
+        None => {
+            if password_stdin {
+                anyhow::bail!("--password-stdin needs --username");
+            }
+            eprint!("Username: ");
+            io::stderr().flush()?;
+            let mut line = String::new();
+            io::stdin().read_line(&mut line)?;
+            line.trim().to_string()
+        }
+    };
+    let password = if password_stdin {
+        let mut password = String::new();
+        io::stdin().read_to_string(&mut password)?;
+        password.trim_end_matches(['\r', '\n']).to_string()
+    } else {
+        read_password_prompt("Password: ")?
+    };
+    let body = serde_json::json!({ "username": username, "password": password });
+    login_request(client.post_json("/auth/login", &body).await?, "Login").await
+}
+
+/// Sign in through the server's OIDC device authorization flow.
+async fn device_login(client: &OqtoClient) -> Result<serde_json::Value> {
+    let start = login_request(
+        client.post("/auth/oidc/device").await?,
+        "Starting single sign-on",
+    )
+    .await?;
+    let device_code = start["device_code"]
+        .as_str()
+        .ok_or_else(|| anyhow!("Sign-on response has no device_code"))?;
+    eprintln!(
+        "Open {} and enter the code {}",
+        start["verification_uri"].as_str().unwrap_or("?"),
+        start["user_code"].as_str().unwrap_or("?")
+    );
+    if let Some(url) = start["verification_uri_complete"].as_str() {
+        eprintln!("or open {url}");
+    }
+    eprintln!("Waiting for you to sign in...");
+
+    let mut interval = start["interval"].as_u64().unwrap_or(5).max(1);
+    let deadline = std::time::Instant::now()
+        + std::time::Duration::from_secs(start["expires_in"].as_u64().unwrap_or(600));
+    let body = serde_json::json!({ "device_code": device_code });
+    loop {
+        tokio::time::sleep(std::time::Duration::from_secs(interval)).await;
+        if std::time::Instant::now() > deadline {
+            anyhow::bail!("The code expired; run oqtoctl login again");
+        }
+        let poll = login_request(
+            client.post_json("/auth/oidc/device/token", &body).await?,
+            "Single sign-on",
+        )
+        .await?;
+        match poll["status"].as_str() {
+            Some("pending") => {}
+            Some("slow_down") => interval += 5,
+            Some("complete") => return Ok(poll),
+            other => anyhow::bail!("Unexpected sign-on status {other:?}"),
+        }
+    }
+}
+
+/// Fail with the server's message unless the request succeeded, otherwise
+/// return the response body.
+async fn login_request(response: OqtoResponse, action: &str) -> Result<serde_json::Value> {
+    if !response.status().is_success() {
+        let status = response.status();
+        let body = response.text().await.unwrap_or_default();
+        anyhow::bail!("{} failed ({}): {}", action, status, body);
+    }
+    Ok(response.json().await?)
+}
+
+/// This machine's name, to recognize the tokens `login` creates.
+fn host_name() -> String {
+    #[cfg(unix)]
+    {
+        let mut buf = [0u8; 256];
+        if unsafe { libc::gethostname(buf.as_mut_ptr().cast(), buf.len()) } == 0 {
+            let len = buf.iter().position(|b| *b == 0).unwrap_or(buf.len());
+            if let Ok(name) = std::str::from_utf8(&buf[..len])
+                && !name.is_empty()
+            {
+                return name.to_string();
+            }
+        }
+    }
+    std::env::var("COMPUTERNAME").unwrap_or_else(|_| "unknown host".to_string())
+}
+
+async fn handle_logout(profile: Option<&str>, json: bool) -> Result<()> {
+    let mut store = ProfileStore::load()?;
+    let Some((name, selected)) = store.selected(profile)? else {
+        anyhow::bail!("Not logged in; name a profile with --profile");
+    };
+    let selected = selected.clone();
+
+    let mut revoked = false;
+    if let Some(id) = selected.token_id.as_deref() {
+        match selected.load_token(&name) {
+            Ok(token) => {
+                let client = OqtoClient::http(&selected.server, Some(token));
+                let path = format!("/tokens/{}", urlencoding::encode(id));
+                match client.delete(&path).await {
+                    Ok(response) if response.status().is_success() => revoked = true,
+                    Ok(response) => eprintln!(
+                        "Warning: revoking token {} failed ({}); revoke it with oqtoctl tokens revoke",
+                        id,
+                        response.status()
+                    ),
+                    Err(err) => eprintln!(
+                        "Warning: could not reach {} to revoke token {}: {err:#}",
+                        selected.server, id
+                    ),
+                }
+            }
+            Err(err) => eprintln!("Warning: {err:#}"),
+        }
+    }
+    if let Err(err) = profiles::delete_token(&name, &selected) {
+        eprintln!("Warning: removing the token from the keychain failed: {err:#}");
+    }
+    store.profiles.remove(&name);
+    if store.current.as_deref() == Some(name.as_str()) {
+        store.current = None;
+    }
+    store.save()?;
+
+    if json {
+        println!(
+            "{}",
+            serde_json::json!({ "profile": name, "token_revoked": revoked })
+        );
+    } else {
+        println!("Logged out of {} (profile {})", selected.server, name);
+    }
+    Ok(())
+}
+
+fn handle_profiles(command: ProfilesCommand, json: bool) -> Result<()> {
+    let mut store = ProfileStore::load()?;
+    match command {
+        ProfilesCommand::List => {
+            if json {
+                let profiles: Vec<serde_json::Value> = store
+                    .profiles
+                    .iter()
+                    .map(|(name, profile)| {
+                        serde_json::json!({
+                            "name": name,
+                            "current": store.current.as_deref() == Some(name.as_str()),
+                            "server": profile.server,
+                            "user": profile.user,
+                            "storage": profile.storage,
+                            "expires_at": profile.expires_at,
+                        })
+                    })
+                    .collect();
+                println!("{}", serde_json::to_string(&profiles)?);
+                return Ok(());
+            }
+            if store.profiles.is_empty() {
+                println!("No profiles; run oqtoctl login <url>");
+            }
+            for (name, profile) in &store.profiles {
+                let marker = if store.current.as_deref() == Some(name.as_str()) {
+                    "*"
+                } else {
+                    " "
+                };
+                println!(
+                    "{} {:<16} {:<40} {:<28} {}",
+                    marker,
+                    name,
+                    profile.server,
+                    profile.user.as_deref().unwrap_or("-"),
+                    profile.storage
+                );
+            }
+        }
+        ProfilesCommand::Use { name } => {
+            if !store.profiles.contains_key(&name) {
+                anyhow::bail!(
+                    "No profile named '{name}'; run oqtoctl --profile {name} login <url>"
+                );
+            }
+            store.current = Some(name.clone());
+            store.save()?;
+            if json {
+                println!("{}", serde_json::json!({ "current": name }));
+            } else {
+                println!("Using profile {}", name);
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Fail with the server's message unless the request succeeded, otherwise
+/// return the response body.
+async fn schedules_request(response: OqtoResponse, action: &str) -> Result<serde_json::Value> {
+    if !response.status().is_success() {
+        let status = response.status();
+        let body = response.text().await.unwrap_or_default();
+        anyhow::bail!("{} schedule failed ({}): {}", action, status, body);
+    }
+    Ok(response.json().await?)
+}
+
+fn print_schedule_line(schedule: &serde_json::Value) {
+    let next = if schedule["enabled"].as_bool().unwrap_or(false) {
+        schedule["next_run_at"].as_str().unwrap_or("-")
+    } else {
+        "disabled"
+    };
+    println!(
+        "{:<24} {:<16} next: {:<26} {}",
+        schedule["name"].as_str().unwrap_or("?"),
+        schedule["cron"].as_str().unwrap_or("?"),
+        next,
+        schedule["workspace_path"].as_str().unwrap_or("")
+    );
+}
+
 async fn handle_ui(client: &OqtoClient, command: UiCommand, json: bool) -> Result<()> {
     match command {
         UiCommand::Navigate { path, replace } => {
@@ -0,0 +1,384 @@
+//! Saved server profiles of `oqtoctl login`.
+//!
+//! Profiles live in `<config dir>/oqto/profiles.toml`, readable only by the
+//! user: per profile the server URL, who signed in and the ID of the
+//! personal access token created for this machine. The token itself goes
+//! to the OS keychain, the Secret Service through `secret-tool` on Linux
+//! and the login keychain through `security` on macOS. Without one it is
+//! kept in the profile file.
+
+use std::collections::BTreeMap;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+use anyhow::{Context, Result, anyhow, bail};
+use serde::{Deserialize, Serialize};
+
+/// Profile `login` writes when `--profile` is not given.
+pub const DEFAULT_PROFILE: &str = "default";
+
+/// Keychain service the tokens are stored under, with the profile name as
+/// the account.
+const KEYCHAIN_SERVICE: &str = "oqtoctl";
+
+/// Where a profile's token is kept.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TokenStorage {
+    Keychain,
+    File,
+}
+
+impl std::fmt::Display for TokenStorage {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::Keychain => "keychain",
+            Self::File => "file",
+        })
+    }
+}
+
+/// A server signed in to.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Profile {
+    /// API base URL, ending in `/api`.
+    pub server: String,
+    /// Email of the user the token belongs to.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub user: Option<String>,
+    /// ID of the token, to revoke it on logout.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub token_id: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expires_at: Option<String>,
+    pub storage: TokenStorage,
+    /// The token, when `storage` is `file`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub token: Option<String>,
+}
+
+impl Profile {
+    /// The profile's token, from the keychain or the file.
+    pub fn load_token(&self, name: &str) -> Result<String> {
+        match self.storage {
+            TokenStorage::File => self
+                .token
+                .clone()
+                .ok_or_else(|| anyhow!("profile '{name}' has no token; run oqtoctl login again")),
+            TokenStorage::Keychain => keychain_load(name).with_context(|| {
+                format!("reading the token of profile '{name}' from the keychain")
+            }),
+        }
+    }
+}
+
+/// All saved profiles.
+#[derive(Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ProfileStore {
+    /// Profile used when `--profile` is not given.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub current: Option<String>,
+    #[serde(default)]
+    pub profiles: BTreeMap<String, Profile>,
+}
+
+impl ProfileStore {
+    pub fn path() -> Result<PathBuf> {
+        let dir = dirs::config_dir().ok_or_else(|| anyhow!("no config directory"))?;
+        Ok(dir.join("oqto").join("profiles.toml"))
+    }
+
+    pub fn load() -> Result<Self> {
+        Self::load_from(&Self::path()?)
+    }
+
+    pub fn load_from(path: &Path) -> Result<Self> {
+        match std::fs::read_to_string(path) {
+            Ok(text) => {
+                toml::from_str(&text).with_context(|| format!("parsing {}", path.display()))
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(e) => Err(e).with_context(|| format!("reading {}", path.display())),
+        }
+    }
+
+    pub fn save(&self) -> Result<()> {
+        self.save_to(&Self::path()?)
+    }
+
+    /// Write the file readable only by the user, replacing it atomically.
+    pub fn save_to(&self, path: &Path) -> Result<()> {
+        let dir = path
+            .parent()
+            .ok_or_else(|| anyhow!("invalid profile path {}", path.display()))?;
+        std::fs::create_dir_all(dir).with_context(|| format!("creating {}", dir.display()))?;
+        let text = toml::to_string_pretty(self).context("serializing profiles")?;
+        let tmp = path.with_extension("toml.tmp");
+        let mut options = std::fs::OpenOptions::new();
+        options.write(true).create(true).truncate(true);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::OpenOptionsExt;
+            options.mode(0o600);
+        }
+        let mut file = options
+            .open(&tmp)
+            .with_context(|| format!("writing {}", tmp.display()))?;
+        file.write_all(text.as_bytes())?;
+        file.sync_all()?;
+        std::fs::rename(&tmp, path).with_context(|| format!("writing {}", path.display()))
+    }
+
+    /// The profile named by `--profile`, or the current one. Fails when a
+    /// named profile does not exist.
+    pub fn selected(&self, name: Option<&str>) -> Result<Option<(String, &Profile)>> {
+        match name {
+            Some(name) => match self.profiles.get(name) {
+                Some(profile) => Ok(Some((name.to_string(), profile))),
+                None => bail!("no profile named '{name}'; run oqtoctl --profile {name} login"),
+            },
+            None => Ok(self
+                .current
+                .as_deref()
+                .and_then(|name| Some((name.to_string(), self.profiles.get(name)?)))),
+        }
+    }
+}
+
+/// Profile names double as keychain account names; keep them plain.
+pub fn validate_name(name: &str) -> Result<()> {
+    if name.is_empty()
+        || name.len() > 64
+        || !name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'))
+    {
+        bail!("profile names may only contain letters, digits, '-', '_' and '.'");
+    }
+    Ok(())
+}
+
+/// The API base URL of a server: `https://oqto.example.com` becomes
+/// `https://oqto.example.com/api`.
+pub fn normalize_server_url(url: &str) -> Result<String> {
+    let url = url.trim().trim_end_matches('/');
+    let parsed = reqwest::Url::parse(url).with_context(|| format!("invalid server URL '{url}'"))?;
+    if !matches!(parsed.scheme(), "http" | "https") {
+        bail!("server URL must start with http:// or https://");
+    }
+    if url.ends_with("/api") {
+        Ok(url.to_string())
+    } else {
+        Ok(format!("{url}/api"))
+    }
+}
+
+/// Store a profile's token in the keychain. Returns false when no keychain
+/// is available; the caller then keeps it in the profile file.
+pub fn store_token(name: &str, token: &str) -> Result<bool> {
+    if token.is_empty()
+        || token
+            .chars()
+            .any(|c| c.is_whitespace() || c == '"' || c == '\\')
+    {
+        bail!("malformed token");
+    }
+    Ok(keychain_store(name, token).is_ok())
+}
+
+/// Remove a profile's token from the keychain, if it is kept there.
+pub fn delete_token(name: &str, profile: &Profile) -> Result<()> {
+    match profile.storage {
+        TokenStorage::File => Ok(()),
+        TokenStorage::Keychain => keychain_delete(name),
+    }
+}
+
+/// Run a keychain tool, optionally feeding it `input`, and return its
+/// output.
+fn keychain_tool(program: &str, args: &[&str], input: Option<&str>) -> Result<String> {
+    let mut child = Command::new(program)
+        .args(args)
+        .stdin(if input.is_some() {
+            Stdio::piped()
+        } else {
+            Stdio::null()
+        })
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .with_context(|| format!("running {program}"))?;
+    if let (Some(input), Some(mut stdin)) = (input, child.stdin.take()) {
+        stdin.write_all(input.as_bytes())?;
+    }
+    let output = child.wait_with_output()?;
+    if !output.status.success() {
+        bail!(
+            "{program} failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+// The token goes through stdin so it never shows up in the process list.
+
+#[cfg(target_os = "macos")]
+fn keychain_store(name: &str, token: &str) -> Result<()> {
+    let command =
+        format!("add-generic-password -U -s {KEYCHAIN_SERVICE} -a \"{name}\" -w \"{token}\"\n");
+    keychain_tool("security", &["-i"], Some(&command)).map(drop)
+}
+
+#[cfg(target_os = "macos")]
+fn keychain_load(name: &str) -> Result<String> {
+    keychain_tool(
+        "security",
+        &[
+            "find-generic-password",
+            "-s",
+            KEYCHAIN_SERVICE,
+            "-a",
+            name,
+            "-w",
+        ],
+        None,
+    )
+}
+
+#[cfg(target_os = "macos")]
+fn keychain_delete(name: &str) -> Result<()> {
+    keychain_tool(
+        "security",
+        &[
+            "delete-generic-password",
+            "-s",
+            KEYCHAIN_SERVICE,
+            "-a",
+            name,
+        ],
+        None,
+    )
+    .map(drop)
+}
+
+#[cfg(not(target_os = "macos"))]
+fn keychain_store(name: &str, token: &str) -> Result<()> {
+    let label = format!("oqtoctl profile {name}");
+    keychain_tool(
+        "secret-tool",
+        &[
+            "store",
+            "--label",
+            &label,
+            "service",
+            KEYCHAIN_SERVICE,
+            "profile",
+            name,
+        ],
+        Some(token),
+    )
+    .map(drop)
+}
+
+#[cfg(not(target_os = "macos"))]
+fn keychain_load(name: &str) -> Result<String> {
+    let token = keychain_tool(
+        "secret-tool",
+        &["lookup", "service", KEYCHAIN_SERVICE, "profile", name],
+        None,
+    )?;
+    if token.is_empty() {
+        bail!("no token stored; run oqtoctl login again");
+    }
+    Ok(token)
+}
+
+#[cfg(not(target_os = "macos"))]
+fn keychain_delete(name: &str) -> Result<()> {
+    keychain_tool(
+        "secret-tool",
+        &["clear", "service", KEYCHAIN_SERVICE, "profile", name],
+        None,
+    )
+    .map(drop)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn profile(server: &str) -> Profile {
+        Profile {
+            server: server.to_string(),
+            user: Some("alice@example.com".to_string()),
+            token_id: Some("key_1".to_string()),
+            expires_at: None,
+            storage: TokenStorage::File,
+            token: Some("oqto_abc".to_string()),
+        }
+    }
+
+    #[test]
+    fn server_urls_are_normalized() {
+        assert_eq!(
+            normalize_server_url("https://oqto.example.com/").unwrap(),
+            "https://oqto.example.com/api"
+        );
+        assert_eq!(
+            normalize_server_url("http://localhost:8080/api").unwrap(),
+            "http://localhost:8080/api"
+        );
+        assert!(normalize_server_url("oqto.example.com").is_err());
+        assert!(normalize_server_url("ftp://oqto.example.com").is_err());
+    }
+
+    #[test]
+    fn profile_names_are_plain() {
+        assert!(validate_name("work").is_ok());
+        assert!(validate_name("lab-2.eu_west").is_ok());
+        assert!(validate_name("").is_err());
+        assert!(validate_name("a b").is_err());
+        assert!(validate_name("x\"y").is_err());
+    }
+
+    #[test]
+    fn store_round_trips_and_selects_profiles() {
+        let path = std::env::temp_dir()
+            .join(format!("oqtoctl-profiles-{}", nanoid::nanoid!()))
+            .join("profiles.toml");
+        assert_eq!(
+            ProfileStore::load_from(&path).unwrap(),
+            ProfileStore::default()
+        );
+
+        let mut store = ProfileStore::default();
+        store
+            .profiles
+            .insert("work".to_string(), profile("https://work.example.com/api"));
+        store
+            .profiles
+            .insert("lab".to_string(), profile("https://lab.example.com/api"));
+        store.current = Some("work".to_string());
+        store.save_to(&path).unwrap();
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o600);
+        }
+
+        let loaded = ProfileStore::load_from(&path).unwrap();
+        assert_eq!(loaded, store);
+        let (name, selected) = loaded.selected(None).unwrap().unwrap();
+        assert_eq!(name, "work");
+        assert_eq!(selected.load_token(&name).unwrap(), "oqto_abc");
+        let (name, _) = loaded.selected(Some("lab")).unwrap().unwrap();
+        assert_eq!(name, "lab");
+        assert!(loaded.selected(Some("home")).is_err());
+        assert!(ProfileStore::default().selected(None).unwrap().is_none());
+
+        std::fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+}
@@ -12,7 +12,10 @@ import {
 	getToolIcon,
 } from "@/components/chat";
 import { BrailleSpinner } from "@/components/common";
-import { useChatContext } from "@/components/contexts/chat-context";
+import {
+	sharedWorkspaceSessionMap,
+	useChatContext,
+} from "@/components/contexts/chat-context";
 import {
 	ContextWindowGauge,
 	CopyButton,
@@ -65,6 +68,7 @@ import {
 	setCachedScrollPosition,
 	useChat,
 } from "@/hooks/useChat";
+import { bookmarkUrl, createBookmark } from "@/lib/api/bookmarks";
 import { workspaceFileUrl } from "@/lib/api/files";
 import type { Part, ToolStatus } from "@/lib/canonical-types";
 import { useChatVerbosity } from "@/lib/chat-verbosity";
@@ -94,6 +98,7 @@ import { getWsManager } from "@/lib/ws-manager";
 import {
 	ArrowDown,
 	ArrowUp,
+	Bookmark,
 	Bot,
 	Check,
 	Copy,
@@ -485,6 +490,38 @@ export function ChatView({
 		[selectedSessionId, refresh, onSelectedSessionIdChange],
 	);
 
+	const handleBookmarkAt = useCallback(
+		async (messageId: string, text: string) => {
+			if (!selectedSessionId) {
+				toast.error("No active chat session.");
+				return;
+			}
+			const firstLine = text.trim().split("\n")[0] ?? "";
+			const label =
+				(firstLine.length > 80 ? `${firstLine.slice(0, 80)}…` : firstLine) ||
+				"Bookmark";
+			const messageIndex = messages.findIndex((m) => m.id === messageId);
+			try {
+				const bookmark = await createBookmark(
+					selectedSessionId,
+					{
+						label,
+						message_id: messageId,
+						message_index: messageIndex >= 0 ? messageIndex : undefined,
+					},
+					sharedWorkspaceSessionMap.get(selectedSessionId),
+				);
+				await navigator.clipboard?.writeText(bookmarkUrl(bookmark));
+				toast.success("Bookmark created. Link copied.");
+			} catch (err) {
+				toast.error(
+					err instanceof Error ? err.message : "Failed to create bookmark.",
+				);
+			}
+		},
+		[selectedSessionId, messages],
+	);
+
 	// Consume pending chat input from external source (e.g. browser "Send to chat")
 	// biome-ignore lint/correctness/useExhaustiveDependencies: setInput is stable setState
 	useEffect(() => {
@@ -2495,6 +2532,13 @@ export function ChatView({
 																	}
 																: undefined
 														}
+														onBookmark={
+															groupMessageId &&
+															!groupMessageId.startsWith("history-")
+																? (text) =>
+																		void handleBookmarkAt(groupMessageId, text)
+																: undefined
+														}
 														onFileReferenceOpen={onFileReferenceOpen}
 														hideRecoveredErrors={hideRecoveredErrors}
 														freezeStreamingUpdates={isUserScrolled}
@@ -3428,6 +3472,7 @@ const MessageGroupCard = memo(function MessageGroupCard({
 	messageId,
 	showWorkingIndicator = false,
 	onForkHere,
+	onBookmark,
 	onFileReferenceOpen,
 	hideRecoveredErrors = false,
 	freezeStreamingUpdates = false,
@@ -3443,6 +3488,7 @@ const MessageGroupCard = memo(function MessageGroupCard({
 	messageId?: string;
 	showWorkingIndicator?: boolean;
 	onForkHere?: () => void;
+	onBookmark?: (text: string) => void;
 	onFileReferenceOpen?: (filePath: string) => void;
 	hideRecoveredErrors?: boolean;
 	freezeStreamingUpdates?: boolean;
@@ -4350,6 +4396,15 @@ const MessageGroupCard = memo(function MessageGroupCard({
 						{t("chat.forkHere", "Fork here")}
 					</ContextMenuItem>
 				)}
+				{onBookmark && (
+					<ContextMenuItem
+						onClick={() => onBookmark(allTextContent ?? "")}
+						className="gap-2"
+					>
+						<Bookmark className="w-4 h-4" />
+						{t("chat.bookmark", "Bookmark")}
+					</ContextMenuItem>
+				)}
 				{allTextContent && (
 					<ContextMenuItem
 						onClick={() => navigator.clipboard?.writeText(allTextContent)}
@@ -99,6 +99,41 @@ export const upsertToolResultPart = ({
 	return created;
 };
 
+/**
+ * Append streamed output of a running tool to its result part. The part's
+ * output is replaced by the final result on `tool.end`.
+ */
+export const appendToolOutputDelta = ({
+	message,
+	toolCallId,
+	name,
+	delta,
+	nextPartId,
+}: {
+	message: DisplayMessage;
+	toolCallId: string;
+	name?: string;
+	delta: string;
+	nextPartId: () => string;
+}): void => {
+	const existing = message.parts.find(
+		(part) => part.type === "tool_result" && part.toolCallId === toolCallId,
+	);
+	if (existing && existing.type === "tool_result") {
+		existing.output =
+			typeof existing.output === "string" ? existing.output + delta : delta;
+		return;
+	}
+	message.parts.push({
+		type: "tool_result",
+		id: nextPartId(),
+		toolCallId,
+		name,
+		output: delta,
+		isError: false,
+	});
+};
+
 export const replaceCompactionPlaceholder = ({
 	message,
 	replacement,
@@ -3,6 +3,7 @@ import type { Dispatch, MutableRefObject, SetStateAction } from "react";
 
 import {
 	appendDeltaPart,
+	appendToolOutputDelta,
 	replaceCompactionPlaceholder,
 	upsertToolCallPart,
 	upsertToolResultPart,
@@ -176,6 +177,25 @@ export const dispatchStreamToolEvent = ({
 			return true;
 		}
 
+		case "tool.output_delta": {
+			const toolCallId =
+				typeof event.tool_call_id === "string" ? event.tool_call_id : "";
+			const delta = typeof event.delta === "string" ? event.delta : "";
+			if (!toolCallId) return true;
+			const targetMessage = ensureAssistantMessage(true);
+			appendToolOutputDelta({
+				message: targetMessage,
+				toolCallId,
+				name: event.name as string,
+				delta: event.truncated
+					? `${delta}\n[output truncated, full result follows when the tool finishes]`
+					: delta,
+				nextPartId,
+			});
+			throttledStreamingUpdate(targetMessage);
+			return true;
+		}
+
 		case "tool.end": {
 			const toolCallId =
 				typeof event.tool_call_id === "string" ? event.tool_call_id : "";
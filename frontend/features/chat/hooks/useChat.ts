@@ -909,6 +909,7 @@ export function useChat(options: UseChatOptions = {}): UseChatReturn {
 				case "stream.tool_call_start":
 				case "stream.tool_call_end":
 				case "tool.start":
+				case "tool.output_delta":
 				case "tool.end": {
 					dispatchStreamToolEvent({
 						event,
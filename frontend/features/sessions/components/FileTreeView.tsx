@@ -24,6 +24,7 @@ import {
 	deletePathMux,
 	downloadPathMux,
 	downloadZipMux,
+	estimateZipMux,
 	fetchFileTreeMux,
 	movePathMux,
 	renamePathMux,
@@ -1145,9 +1146,29 @@ export function FileTreeView({
 		],
 	);
 
-	const handleDownload = (path: string, _isDirectory: boolean) => {
+	// Directories are zipped; check the size first so a download over the
+	// fileserver limits fails with a readable message instead of a broken file.
+	const checkZipEstimate = async (workspacePath: string, paths: string[]) => {
+		const estimate = await estimateZipMux(workspacePath, paths);
+		if (!estimate.within_limits) {
+			throw new Error(
+				`Archive too large: ${estimate.files} files, ${formatFileSize(estimate.bytes)} after exclusions`,
+			);
+		}
+	};
+
+	const handleDownload = (path: string, isDirectory: boolean) => {
 		if (!normalizedWorkspacePath || !cacheKey) return;
-		void downloadPathMux(normalizedWorkspacePath, path);
+		void (async () => {
+			try {
+				if (isDirectory) {
+					await checkZipEstimate(normalizedWorkspacePath, [path]);
+				}
+				await downloadPathMux(normalizedWorkspacePath, path);
+			} catch (err) {
+				setError(err instanceof Error ? err.message : "Download failed");
+			}
+		})();
 	};
 
 	const handleDownloadSelected = () => {
@@ -1163,6 +1184,7 @@ export function FileTreeView({
 				}
 
 				const zipName = `selection-${new Date().toISOString().slice(0, 10)}.zip`;
+				await checkZipEstimate(normalizedWorkspacePath, selectedPaths);
 				await downloadZipMux(normalizedWorkspacePath, selectedPaths, zipName);
 			} catch (err) {
 				setError(err instanceof Error ? err.message : "Download failed");
@@ -0,0 +1,57 @@
+/**
+ * Tool Approvals API
+ * Approving or denying agent tool calls paused by `[runner.tool_approvals]`
+ */
+
+import { authFetch, controlPlaneApiUrl, readApiError } from "./client";
+
+/** A tool call waiting for approval */
+export type ToolApproval = {
+	approval_id: string;
+	tool_call_id: string;
+	/** Tool name */
+	name: string;
+	/** Tool arguments */
+	input: unknown;
+	/** Approval rule the call matched */
+	rule: string;
+	/** Unix ms */
+	requested_at: number;
+	/** Unix ms; the call is denied after this */
+	expires_at: number;
+};
+
+function approvalsUrl(sessionId: string, approvalId?: string) {
+	const base = `/api/sessions/${encodeURIComponent(sessionId)}/approvals`;
+	return controlPlaneApiUrl(
+		approvalId ? `${base}/${encodeURIComponent(approvalId)}` : base,
+	);
+}
+
+/** Tool calls of a session waiting for approval, oldest first */
+export async function listToolApprovals(
+	sessionId: string,
+): Promise<ToolApproval[]> {
+	const res = await authFetch(approvalsUrl(sessionId), {
+		credentials: "include",
+	});
+	if (!res.ok) throw new Error(await readApiError(res));
+	return res.json();
+}
+
+/** Approve or deny a paused tool call; a denial reason is passed to the agent */
+export async function resolveToolApproval(
+	sessionId: string,
+	approvalId: string,
+	approved: boolean,
+	reason?: string,
+): Promise<ToolApproval> {
+	const res = await authFetch(approvalsUrl(sessionId, approvalId), {
+		method: "POST",
+		headers: { "Content-Type": "application/json" },
+		body: JSON.stringify({ approved, reason }),
+		credentials: "include",
+	});
+	if (!res.ok) throw new Error(await readApiError(res));
+	return res.json();
+}
@@ -0,0 +1,109 @@
+/**
+ * Session Bookmarks API
+ * Named points in a session's timeline, each with a deep link
+ */
+
+import { authFetch, controlPlaneApiUrl, readApiError } from "./client";
+
+/** A named point in a session's timeline */
+export type SessionBookmark = {
+	id: string;
+	session_id: string;
+	/** Who created the bookmark */
+	user_id: string;
+	/** Shared workspace of the session (bookmark is visible to its members) */
+	workspace_id: string | null;
+	label: string;
+	/** Message the bookmark points at */
+	message_id: string;
+	/** Position of the message in the transcript, if known */
+	message_index: number | null;
+	created_at: string;
+	/** Frontend path that opens the session at the message */
+	link: string;
+};
+
+export type CreateBookmarkRequest = {
+	label: string;
+	message_id: string;
+	message_index?: number;
+};
+
+function sessionBookmarksUrl(sessionId: string, sharedWorkspaceId?: string) {
+	const params = new URLSearchParams();
+	if (sharedWorkspaceId) params.set("shared_workspace_id", sharedWorkspaceId);
+	const qs = params.toString();
+	return controlPlaneApiUrl(
+		`/api/sessions/${encodeURIComponent(sessionId)}/bookmarks${qs ? `?${qs}` : ""}`,
+	);
+}
+
+/** List a session's bookmarks in timeline order */
+export async function listBookmarks(
+	sessionId: string,
+	sharedWorkspaceId?: string,
+): Promise<SessionBookmark[]> {
+	const res = await authFetch(sessionBookmarksUrl(sessionId, sharedWorkspaceId), {
+		credentials: "include",
+	});
+	if (!res.ok) throw new Error(await readApiError(res));
+	return res.json();
+}
+
+/** Bookmark a message in a session */
+export async function createBookmark(
+	sessionId: string,
+	request: CreateBookmarkRequest,
+	sharedWorkspaceId?: string,
+): Promise<SessionBookmark> {
+	const res = await authFetch(sessionBookmarksUrl(sessionId, sharedWorkspaceId), {
+		method: "POST",
+		headers: { "Content-Type": "application/json" },
+		body: JSON.stringify(request),
+		credentials: "include",
+	});
+	if (!res.ok) throw new Error(await readApiError(res));
+	return res.json();
+}
+
+/** Resolve a bookmark (e.g. from a deep link) */
+export async function getBookmark(bookmarkId: string): Promise<SessionBookmark> {
+	const res = await authFetch(
+		controlPlaneApiUrl(`/api/bookmarks/${encodeURIComponent(bookmarkId)}`),
+		{ credentials: "include" },
+	);
+	if (!res.ok) throw new Error(await readApiError(res));
+	return res.json();
+}
+
+/** Rename a bookmark */
+export async function renameBookmark(
+	bookmarkId: string,
+	label: string,
+): Promise<SessionBookmark> {
+	const res = await authFetch(
+		controlPlaneApiUrl(`/api/bookmarks/${encodeURIComponent(bookmarkId)}`),
+		{
+			method: "PATCH",
+			headers: { "Content-Type": "application/json" },
+			body: JSON.stringify({ label }),
+			credentials: "include",
+		},
+	);
+	if (!res.ok) throw new Error(await readApiError(res));
+	return res.json();
+}
+
+/** Delete a bookmark */
+export async function deleteBookmark(bookmarkId: string): Promise<void> {
+	const res = await authFetch(
+		controlPlaneApiUrl(`/api/bookmarks/${encodeURIComponent(bookmarkId)}`),
+		{ method: "DELETE", credentials: "include" },
+	);
+	if (!res.ok) throw new Error(await readApiError(res));
+}
+
+/** Absolute URL of a bookmark's deep link, for sharing */
+export function bookmarkUrl(bookmark: SessionBookmark): string {
+	return new URL(bookmark.link, window.location.origin).toString();
+}
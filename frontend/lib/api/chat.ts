@@ -77,6 +77,8 @@ export type ChatHistoryQuery = {
 	limit?: number;
 	/** If set, list sessions from this shared workspace instead of personal */
 	shared_workspace_id?: string;
+	/** Only sessions carrying all of these tags */
+	tags?: string[];
 };
 
 /** Request to update a chat session */
@@ -108,6 +110,7 @@ export async function listChatHistory(
 	if (query.limit) url.searchParams.set("limit", query.limit.toString());
 	if (query.shared_workspace_id)
 		url.searchParams.set("shared_workspace_id", query.shared_workspace_id);
+	if (query.tags?.length) url.searchParams.set("tags", query.tags.join(","));
 
 	const res = await authFetch(url.toString(), {
 		cache: "no-store",
@@ -132,6 +135,7 @@ export async function listChatHistoryGrouped(
 	if (query.workspace) url.searchParams.set("workspace", query.workspace);
 	if (query.include_children) url.searchParams.set("include_children", "true");
 	if (query.limit) url.searchParams.set("limit", query.limit.toString());
+	if (query.tags?.length) url.searchParams.set("tags", query.tags.join(","));
 
 	const res = await authFetch(url.toString(), {
 		cache: "no-store",
@@ -3,7 +3,8 @@
  * Feature flags and configuration
  */
 
-import { authFetch, controlPlaneApiUrl } from "./client";
+import { authFetch, controlPlaneApiUrl, readApiError } from "./client";
+import type { SubsystemCapability } from "../ws-mux-types";
 
 // ============================================================================
 // Features Types
@@ -17,6 +18,8 @@ export type VisualizerVoiceConfig = {
 
 /** Voice configuration from backend */
 export type VoiceFeatureConfig = {
+	/** Whether the voice services are reachable (updated by voice.availability events) */
+	available: boolean;
 	stt_url: string;
 	tts_url: string;
 	vad_timeout_ms: number;
@@ -43,6 +46,18 @@ export type Features = {
 	websocket_events?: boolean;
 	/** Whether the agent-browser integration is enabled */
 	agent_browser_enabled?: boolean;
+	/** Whether users can register without an invite code */
+	open_registration?: boolean;
+	/** Single sign-on through an OpenID Connect provider (absent if disabled) */
+	oidc?: OidcFeature;
+};
+
+/** Single sign-on offered on the login page */
+export type OidcFeature = {
+	/** Text of the sign-in button */
+	label: string;
+	/** Starts the sign-in; takes a `return_to` path */
+	login_url: string;
 };
 
 // ============================================================================
@@ -64,3 +79,41 @@ export async function getFeatures(): Promise<Features> {
 	}
 	return res.json();
 }
+
+/** State of the optional subsystems, with reasons for disabled/degraded ones */
+export async function getCapabilities(): Promise<SubsystemCapability[]> {
+	const res = await authFetch(controlPlaneApiUrl("/api/meta/capabilities"), {
+		credentials: "include",
+	});
+	if (!res.ok) throw new Error(await readApiError(res));
+	const body: { capabilities: SubsystemCapability[] } = await res.json();
+	return body.capabilities;
+}
+
+/** Last probe result of one voice endpoint */
+export type VoiceEndpointHealth = {
+	url: string;
+	reachable: boolean;
+	last_error?: string;
+	consecutive_failures: number;
+	checked_at: string;
+};
+
+/** Cached health of the voice services */
+export type VoiceHealth = {
+	enabled: boolean;
+	available: boolean;
+	/** Why voice is unavailable */
+	reason?: string;
+	stt?: VoiceEndpointHealth;
+	tts?: VoiceEndpointHealth;
+};
+
+/** Health of the STT/TTS services as last probed by the backend */
+export async function getVoiceHealth(): Promise<VoiceHealth> {
+	const res = await authFetch(controlPlaneApiUrl("/api/meta/voice"), {
+		credentials: "include",
+	});
+	if (!res.ok) throw new Error(await readApiError(res));
+	return res.json();
+}
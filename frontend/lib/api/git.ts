@@ -0,0 +1,209 @@
+/**
+ * Workspace Git API
+ * Status, diffs, history, commits and pushes of a workspace's git repository
+ */
+
+import { authFetch, controlPlaneApiUrl, readApiError } from "./client";
+
+export type GitChangeKind =
+	| "added"
+	| "modified"
+	| "deleted"
+	| "renamed"
+	| "copied"
+	| "type_changed"
+	| "untracked"
+	| "conflicted";
+
+/** A changed file */
+export type GitStatusEntry = {
+	/** Path relative to the repository root */
+	path: string;
+	/** Path before a rename or copy */
+	orig_path?: string;
+	/** Change staged in the index */
+	staged?: GitChangeKind;
+	/** Change in the working tree not staged yet */
+	unstaged?: GitChangeKind;
+};
+
+export type GitStatus = {
+	/** Current branch; null for a detached HEAD */
+	branch: string | null;
+	/** Commit of HEAD; null before the first commit */
+	head: string | null;
+	upstream: string | null;
+	ahead: number;
+	behind: number;
+	entries: GitStatusEntry[];
+};
+
+export type GitDiff = {
+	diff: string;
+	/** The diff was cut off at the size limit */
+	truncated: boolean;
+};
+
+export type GitCommit = {
+	commit: string;
+	author_name: string;
+	author_email: string;
+	/** Author date (Unix ms) */
+	timestamp: number;
+	subject: string;
+};
+
+export type GitBranch = {
+	name: string;
+	commit: string;
+	upstream?: string;
+	current: boolean;
+};
+
+export type GitBranches = {
+	current: string | null;
+	branches: GitBranch[];
+};
+
+export type GitCommitRequest = {
+	message: string;
+	/** Paths to commit; every change when omitted */
+	paths?: string[];
+	author_name?: string;
+	author_email?: string;
+};
+
+export type GitPushRequest = {
+	remote?: string;
+	branch?: string;
+	set_upstream?: boolean;
+};
+
+export type GitPushResult = {
+	remote: string;
+	branch: string;
+	output: string;
+};
+
+/** A stored push credential (the token is never returned) */
+export type GitCredential = {
+	host: string;
+	username: string;
+	created_at: string;
+	updated_at: string;
+};
+
+function gitUrl(workspacePath: string, action: string, params?: URLSearchParams) {
+	const qs = params?.toString();
+	return controlPlaneApiUrl(
+		`/api/workspaces/${encodeURIComponent(workspacePath)}/git/${action}${qs ? `?${qs}` : ""}`,
+	);
+}
+
+async function getJson<T>(url: string): Promise<T> {
+	const res = await authFetch(url, { credentials: "include" });
+	if (!res.ok) throw new Error(await readApiError(res));
+	return res.json();
+}
+
+async function postJson<T>(url: string, body: unknown): Promise<T> {
+	const res = await authFetch(url, {
+		method: "POST",
+		headers: { "Content-Type": "application/json" },
+		body: JSON.stringify(body),
+		credentials: "include",
+	});
+	if (!res.ok) throw new Error(await readApiError(res));
+	return res.json();
+}
+
+/** Branch and changed files of a workspace repository */
+export async function getGitStatus(workspacePath: string): Promise<GitStatus> {
+	return getJson(gitUrl(workspacePath, "status"));
+}
+
+/** Diff of the uncommitted changes, or of one commit */
+export async function getGitDiff(
+	workspacePath: string,
+	options: { path?: string; staged?: boolean; commit?: string } = {},
+): Promise<GitDiff> {
+	const params = new URLSearchParams();
+	if (options.path) params.set("path", options.path);
+	if (options.staged) params.set("staged", "true");
+	if (options.commit) params.set("commit", options.commit);
+	return getJson(gitUrl(workspacePath, "diff", params));
+}
+
+/** Commits, newest first */
+export async function getGitLog(
+	workspacePath: string,
+	options: { limit?: number; path?: string; rev?: string } = {},
+): Promise<GitCommit[]> {
+	const params = new URLSearchParams();
+	if (options.limit) params.set("limit", String(options.limit));
+	if (options.path) params.set("path", options.path);
+	if (options.rev) params.set("rev", options.rev);
+	const log = await getJson<{ commits: GitCommit[] }>(
+		gitUrl(workspacePath, "log", params),
+	);
+	return log.commits;
+}
+
+/** Local branches */
+export async function listGitBranches(workspacePath: string): Promise<GitBranches> {
+	return getJson(gitUrl(workspacePath, "branches"));
+}
+
+/** Check out a branch, creating it with `create` */
+export async function switchGitBranch(
+	workspacePath: string,
+	name: string,
+	create = false,
+): Promise<GitBranches> {
+	return postJson(gitUrl(workspacePath, "branches"), { name, create });
+}
+
+/** Stage and commit changes */
+export async function commitGit(
+	workspacePath: string,
+	request: GitCommitRequest,
+): Promise<GitCommit> {
+	return postJson(gitUrl(workspacePath, "commit"), request);
+}
+
+/** Push a branch with the caller's stored credentials */
+export async function pushGit(
+	workspacePath: string,
+	request: GitPushRequest = {},
+): Promise<GitPushResult> {
+	return postJson(gitUrl(workspacePath, "push"), request);
+}
+
+/** Hosts the caller has push credentials for */
+export async function listGitCredentials(): Promise<GitCredential[]> {
+	return getJson(controlPlaneApiUrl("/api/git/credentials"));
+}
+
+/** Store a push credential (username and access token) for a host */
+export async function setGitCredential(
+	host: string,
+	username: string,
+	token: string,
+): Promise<void> {
+	const res = await authFetch(controlPlaneApiUrl("/api/git/credentials"), {
+		method: "PUT",
+		headers: { "Content-Type": "application/json" },
+		body: JSON.stringify({ host, username, token }),
+		credentials: "include",
+	});
+	if (!res.ok) throw new Error(await readApiError(res));
+}
+
+/** Remove the push credential for a host */
+export async function deleteGitCredential(host: string): Promise<void> {
+	const res = await authFetch(
+		controlPlaneApiUrl(`/api/git/credentials/${encodeURIComponent(host)}`),
+		{ method: "DELETE", credentials: "include" },
+	);
+	if (!res.ok) throw new Error(await readApiError(res));
+}
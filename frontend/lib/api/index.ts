@@ -35,6 +35,7 @@ export type {
 	CreateProjectFromTemplateRequest,
 	CreateWorkspaceSessionRequest,
 	ProjectEntry,
+	ProjectCard,
 	WorkspaceMeta,
 	WorkspaceSandboxConfig,
 	WorkspacePiResources,
@@ -88,6 +89,57 @@ export {
 	convertChatMessagesToCanonical,
 } from "./chat";
 
+// Session bookmarks
+export type { SessionBookmark, CreateBookmarkRequest } from "./bookmarks";
+export {
+	listBookmarks,
+	createBookmark,
+	getBookmark,
+	renameBookmark,
+	deleteBookmark,
+	bookmarkUrl,
+} from "./bookmarks";
+
+// Tool approvals
+export type { ToolApproval } from "./approvals";
+export { listToolApprovals, resolveToolApproval } from "./approvals";
+
+// Workspace git
+export type {
+	GitChangeKind,
+	GitStatusEntry,
+	GitStatus,
+	GitDiff,
+	GitCommit,
+	GitBranch,
+	GitBranches,
+	GitCommitRequest,
+	GitPushRequest,
+	GitPushResult,
+	GitCredential,
+} from "./git";
+export {
+	getGitStatus,
+	getGitDiff,
+	getGitLog,
+	listGitBranches,
+	switchGitBranch,
+	commitGit,
+	pushGit,
+	listGitCredentials,
+	setGitCredential,
+	deleteGitCredential,
+} from "./git";
+
+// Session tags
+export type {
+	SessionTag,
+	SessionTagKind,
+	TagStat,
+	TagStatsQuery,
+} from "./session-tags";
+export { listSessionTags, getTagStats } from "./session-tags";
+
 // Default chat (Pi) APIs
 export type {
 	PiSessionFile,
@@ -112,6 +164,8 @@ export {
 // Projects
 export {
 	listProjects,
+	listProjectCards,
+	getProjectThumbnailUrl,
 	listWorkspaceDirectories,
 	listProjectTemplates,
 	createProjectFromTemplate,
@@ -139,8 +193,10 @@ export type {
 	VoiceFeatureConfig,
 	SessionAutoAttachMode,
 	Features,
+	VoiceEndpointHealth,
+	VoiceHealth,
 } from "./features";
-export { getFeatures } from "./features";
+export { getFeatures, getCapabilities, getVoiceHealth } from "./features";
 
 // Dashboard
 export type {
@@ -156,6 +212,53 @@ export {
 	getCodexBarUsage,
 } from "./dashboard";
 
+// Agent schedules
+export type {
+	AgentSchedule,
+	SaveAgentScheduleRequest,
+	ScheduleRunStatus,
+	ScheduleRun,
+	ScheduleRunPage,
+} from "./schedules";
+export {
+	listAgentSchedules,
+	createAgentSchedule,
+	updateAgentSchedule,
+	deleteAgentSchedule,
+	runAgentSchedule,
+	listScheduleRuns,
+} from "./schedules";
+
+// Inbound triggers
+export type {
+	AgentTrigger,
+	AgentTriggerWithToken,
+	SaveTriggerRequest,
+	TriggerRunStatus,
+	TriggerRun,
+} from "./triggers";
+export {
+	listTriggers,
+	createTrigger,
+	updateTrigger,
+	deleteTrigger,
+	rotateTriggerToken,
+	listTriggerRuns,
+} from "./triggers";
+
+// Usage and spend
+export type {
+	UsageBucket,
+	UsageTotals,
+	UsageGroup,
+	UsageSummary,
+	SessionUsage,
+	UsageSummaryQuery,
+	BudgetLevel,
+	SessionBudget,
+} from "./usage";
+export { getUsageSummary, getSessionUsage, getSessionBudget } from "./usage";
+
 // Files and proxy URLs
 export {
 	agentProxyBaseUrl,
@@ -192,12 +295,20 @@ export type {
 	SettingsValue,
 	SettingsValues,
 	SettingsUpdateRequest,
+	SettingsPatchOperation,
+	SettingsDocument,
+	SettingsPatchRequest,
+	SettingsPatchRecord,
+	SettingsPatchPage,
 } from "./settings";
 export {
 	getSettingsSchema,
 	getSettingsValues,
 	updateSettingsValues,
 	reloadSettings,
+	getSettingsDocument,
+	patchSettingsDocument,
+	getSettingsHistory,
 } from "./settings";
 
 // API keys
@@ -7,6 +7,7 @@ import { authFetch, controlPlaneApiUrl, readApiError } from "./client";
 import type {
 	CreateProjectFromTemplateRequest,
 	ListProjectTemplatesResponse,
+	ProjectCard,
 	ProjectEntry,
 	WorkspaceDirEntry,
 } from "./types";
@@ -21,6 +22,25 @@ export async function listProjects(): Promise<ProjectEntry[]> {
 	return res.json();
 }
 
+/** List project grid cards (title, description, language, activity) */
+export async function listProjectCards(): Promise<ProjectCard[]> {
+	const res = await authFetch(controlPlaneApiUrl("/api/projects/cards"), {
+		credentials: "include",
+	});
+	if (!res.ok) throw new Error(await readApiError(res));
+	return res.json();
+}
+
+/** URL of a project card thumbnail (only for cards with `thumbnail`) */
+export function getProjectThumbnailUrl(projectPath: string): string {
+	const url = new URL(
+		controlPlaneApiUrl("/api/projects/cards/thumbnail"),
+		window.location.origin,
+	);
+	url.searchParams.set("workspace_path", projectPath);
+	return url.toString();
+}
+
 export async function listWorkspaceDirectories(
 	path = ".",
 ): Promise<WorkspaceDirEntry[]> {
@@ -0,0 +1,151 @@
+/**
+ * Agent Schedules API
+ * Prompts the backend runs in a fresh session on a cron schedule
+ */
+
+import { authFetch, controlPlaneApiUrl, readApiError } from "./client";
+
+/** A prompt run in a new session on a cron schedule */
+export type AgentSchedule = {
+	id: string;
+	user_id: string;
+	name: string;
+	/** Five-field cron expression (UTC) */
+	cron: string;
+	workspace_path: string;
+	/** First message, or the arguments of `template` */
+	prompt: string;
+	/** Prompt template run as `/<template> <prompt>` */
+	template: string | null;
+	harness: string | null;
+	provider: string | null;
+	model: string | null;
+	/** Overrides the server's run timeout */
+	timeout_secs: number | null;
+	enabled: boolean;
+	/** Next due run; null while disabled */
+	next_run_at: string | null;
+	last_run_at: string | null;
+	created_at: string;
+	updated_at: string;
+};
+
+export type SaveAgentScheduleRequest = {
+	/** Required on create; taken from the URL on update */
+	name?: string;
+	cron: string;
+	workspace_path: string;
+	prompt: string;
+	template?: string | null;
+	harness?: string | null;
+	provider?: string | null;
+	model?: string | null;
+	timeout_secs?: number | null;
+	enabled?: boolean;
+};
+
+export type ScheduleRunStatus = "running" | "succeeded" | "failed" | "skipped";
+
+/** One run of a schedule */
+export type ScheduleRun = {
+	id: string;
+	user_id: string;
+	schedule_name: string;
+	status: ScheduleRunStatus;
+	scheduled_for: string | null;
+	started_at: string | null;
+	ended_at: string | null;
+	exit_code: number | null;
+	log_path: string | null;
+	cost_usd: number | null;
+	error: string | null;
+	/** Session an agent schedule run used */
+	session_id: string | null;
+	run_at: string;
+};
+
+/** A page of run history, newest first */
+export type ScheduleRunPage = {
+	runs: ScheduleRun[];
+	total: number;
+	limit: number;
+	offset: number;
+	failure_streak: number;
+};
+
+function scheduleUrl(name?: string, suffix = "") {
+	const path = name ? `/api/schedules/${encodeURIComponent(name)}` : "/api/schedules";
+	return controlPlaneApiUrl(`${path}${suffix}`);
+}
+
+/** List the user's agent schedules */
+export async function listAgentSchedules(): Promise<AgentSchedule[]> {
+	const res = await authFetch(scheduleUrl(), { credentials: "include" });
+	if (!res.ok) throw new Error(await readApiError(res));
+	return res.json();
+}
+
+/** Create an agent schedule */
+export async function createAgentSchedule(
+	request: SaveAgentScheduleRequest,
+): Promise<AgentSchedule> {
+	const res = await authFetch(scheduleUrl(), {
+		method: "POST",
+		headers: { "Content-Type": "application/json" },
+		body: JSON.stringify(request),
+		credentials: "include",
+	});
+	if (!res.ok) throw new Error(await readApiError(res));
+	return res.json();
+}
+
+/** Replace an agent schedule's definition */
+export async function updateAgentSchedule(
+	name: string,
+	request: SaveAgentScheduleRequest,
+): Promise<AgentSchedule> {
+	const res = await authFetch(scheduleUrl(name), {
+		method: "PUT",
+		headers: { "Content-Type": "application/json" },
+		body: JSON.stringify(request),
+		credentials: "include",
+	});
+	if (!res.ok) throw new Error(await readApiError(res));
+	return res.json();
+}
+
+/** Delete an agent schedule (its run history is kept) */
+export async function deleteAgentSchedule(name: string): Promise<void> {
+	const res = await authFetch(scheduleUrl(name), {
+		method: "DELETE",
+		credentials: "include",
+	});
+	if (!res.ok) throw new Error(await readApiError(res));
+}
+
+/** Run an agent schedule now; resolves with the started run */
+export async function runAgentSchedule(name: string): Promise<ScheduleRun> {
+	const res = await authFetch(scheduleUrl(name, "/run"), {
+		method: "POST",
+		credentials: "include",
+	});
+	if (!res.ok) throw new Error(await readApiError(res));
+	return res.json();
+}
+
+/** Run history of a schedule, newest first */
+export async function listScheduleRuns(
+	name: string,
+	query: { status?: ScheduleRunStatus; limit?: number; offset?: number } = {},
+): Promise<ScheduleRunPage> {
+	const params = new URLSearchParams();
+	if (query.status) params.set("status", query.status);
+	if (query.limit !== undefined) params.set("limit", String(query.limit));
+	if (query.offset !== undefined) params.set("offset", String(query.offset));
+	const qs = params.toString();
+	const res = await authFetch(scheduleUrl(name, `/runs${qs ? `?${qs}` : ""}`), {
+		credentials: "include",
+	});
+	if (!res.ok) throw new Error(await readApiError(res));
+	return res.json();
+}
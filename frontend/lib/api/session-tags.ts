@@ -0,0 +1,63 @@
+/**
+ * Session Tags API
+ * Topic, language and framework tags assigned from each session's first prompt
+ */
+
+import { authFetch, controlPlaneApiUrl, readApiError } from "./client";
+
+export type SessionTagKind = "topic" | "language" | "framework";
+
+/** A tag on a session */
+export type SessionTag = {
+	session_id: string;
+	tag: string;
+	kind: SessionTagKind;
+	/** Classifier that assigned it: the configured model or keyword rules */
+	source: "model" | "rules";
+	created_at: string;
+};
+
+/** Number of sessions carrying a tag */
+export type TagStat = {
+	tag: string;
+	kind: SessionTagKind;
+	sessions: number;
+};
+
+export type TagStatsQuery = {
+	kind?: SessionTagKind;
+	/** First day to include (YYYY-MM-DD) */
+	since?: string;
+	/** Last day to include (YYYY-MM-DD) */
+	until?: string;
+	limit?: number;
+};
+
+/** Tags of the current user's sessions, keyed by session ID */
+export async function listSessionTags(): Promise<
+	Record<string, SessionTag[]>
+> {
+	const res = await authFetch(controlPlaneApiUrl("/api/session-tags"), {
+		credentials: "include",
+	});
+	if (!res.ok) throw new Error(await readApiError(res));
+	return res.json();
+}
+
+/** Sessions per tag for the current user, most common first */
+export async function getTagStats(
+	query: TagStatsQuery = {},
+): Promise<TagStat[]> {
+	const params = new URLSearchParams();
+	if (query.kind) params.set("kind", query.kind);
+	if (query.since) params.set("since", query.since);
+	if (query.until) params.set("until", query.until);
+	if (query.limit) params.set("limit", query.limit.toString());
+	const qs = params.toString();
+	const res = await authFetch(
+		controlPlaneApiUrl(`/api/analytics/tags${qs ? `?${qs}` : ""}`),
+		{ credentials: "include" },
+	);
+	if (!res.ok) throw new Error(await readApiError(res));
+	return res.json();
+}
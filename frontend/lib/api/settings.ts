@@ -27,6 +27,45 @@ export type SettingsUpdateRequest = {
 	values: Record<string, unknown>;
 };
 
+/** A JSON Patch (RFC 6902) operation; paths are JSON Pointers */
+export type SettingsPatchOperation =
+	| { op: "add" | "replace" | "test"; path: string; value: unknown }
+	| { op: "remove"; path: string }
+	| { op: "move" | "copy"; from: string; path: string };
+
+/** An app's whole settings document with its version */
+export type SettingsDocument = {
+	/** Pass back with a patch to detect concurrent edits */
+	version: string;
+	document: Record<string, unknown>;
+};
+
+/** Request to patch a settings document */
+export type SettingsPatchRequest = {
+	/** Version the patch was made against; omit to skip conflict detection */
+	version?: string;
+	operations: SettingsPatchOperation[];
+};
+
+/** An applied settings patch (sensitive values redacted) */
+export type SettingsPatchRecord = {
+	id: string;
+	app: string;
+	user_id: string;
+	base_version: string;
+	version: string;
+	operations: SettingsPatchOperation[];
+	created_at: string;
+};
+
+/** A page of settings patch history, newest first */
+export type SettingsPatchPage = {
+	patches: SettingsPatchRecord[];
+	total: number;
+	limit: number;
+	offset: number;
+};
+
 // ============================================================================
 // Settings API
 // ============================================================================
@@ -108,3 +147,65 @@ export async function reloadSettings(
 	);
 	if (!res.ok) throw new Error(await readApiError(res));
 }
+
+/** Get an app's whole settings document and its version */
+export async function getSettingsDocument(
+	app: string,
+	workspacePath?: string,
+): Promise<SettingsDocument> {
+	const res = await authFetch(
+		controlPlaneApiUrl(
+			`/api/settings/document?${buildSettingsQuery(app, workspacePath)}`,
+		),
+		{
+			credentials: "include",
+		},
+	);
+	if (!res.ok) throw new Error(await readApiError(res));
+	return res.json();
+}
+
+/**
+ * Apply a JSON Patch to an app's settings document.
+ * Fails with 409 if the document changed since `patch.version`.
+ */
+export async function patchSettingsDocument(
+	app: string,
+	patch: SettingsPatchRequest,
+	workspacePath?: string,
+): Promise<SettingsDocument> {
+	const res = await authFetch(
+		controlPlaneApiUrl(
+			`/api/settings/document?${buildSettingsQuery(app, workspacePath)}`,
+		),
+		{
+			method: "PATCH",
+			headers: { "Content-Type": "application/json" },
+			body: JSON.stringify(patch),
+			credentials: "include",
+		},
+	);
+	if (!res.ok) throw new Error(await readApiError(res));
+	return res.json();
+}
+
+/** Get the patch history of an app's settings document */
+export async function getSettingsHistory(
+	app: string,
+	options: { limit?: number; offset?: number; workspacePath?: string } = {},
+): Promise<SettingsPatchPage> {
+	const params = new URLSearchParams(
+		buildSettingsQuery(app, options.workspacePath),
+	);
+	if (options.limit !== undefined) params.set("limit", String(options.limit));
+	if (options.offset !== undefined)
+		params.set("offset", String(options.offset));
+	const res = await authFetch(
+		controlPlaneApiUrl(`/api/settings/history?${params.toString()}`),
+		{
+			credentials: "include",
+		},
+	);
+	if (!res.ok) throw new Error(await readApiError(res));
+	return res.json();
+}
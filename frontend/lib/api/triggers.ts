@@ -0,0 +1,154 @@
+/**
+ * Inbound Triggers API
+ * Sessions started by webhook calls and emails
+ */
+
+import { authFetch, controlPlaneApiUrl, readApiError } from "./client";
+
+/** Maps external events to a prompt in one of the user's workspaces */
+export type AgentTrigger = {
+	id: string;
+	user_id: string;
+	name: string;
+	/** First characters of the token */
+	token_prefix: string;
+	workspace_path: string;
+	/** Rendered with {{sender}}, {{subject}}, {{body}}, {{payload}} and {{payload.<field>}} */
+	prompt: string;
+	/** Prompt template run as `/<template> <prompt>` */
+	template: string | null;
+	harness: string | null;
+	provider: string | null;
+	model: string | null;
+	/** Overrides the server's run timeout */
+	timeout_secs: number | null;
+	/** Email addresses, @domains and IP addresses events are accepted from */
+	allowed_senders: string[];
+	/** Receives the result of every run as JSON */
+	reply_webhook_url: string | null;
+	/** Email the result back to senders of email events */
+	reply_email: boolean;
+	enabled: boolean;
+	last_fired_at: string | null;
+	created_at: string;
+	updated_at: string;
+};
+
+/** A trigger with its token, returned only on creation and rotation */
+export type AgentTriggerWithToken = AgentTrigger & {
+	token: string;
+	/** Path external services post events to */
+	url: string;
+};
+
+export type SaveTriggerRequest = {
+	/** Required on create; taken from the URL on update */
+	name?: string;
+	workspace_path: string;
+	prompt: string;
+	template?: string | null;
+	harness?: string | null;
+	provider?: string | null;
+	model?: string | null;
+	timeout_secs?: number | null;
+	allowed_senders?: string[];
+	reply_webhook_url?: string | null;
+	reply_email?: boolean;
+	enabled?: boolean;
+};
+
+export type TriggerRunStatus = "queued" | "running" | "succeeded" | "failed";
+
+/** One event handled by a trigger */
+export type TriggerRun = {
+	id: string;
+	trigger_id: string;
+	source: "webhook" | "email";
+	sender: string | null;
+	subject: string | null;
+	status: TriggerRunStatus;
+	session_id: string;
+	error: string | null;
+	/** "sent", or why the result could not be delivered */
+	delivery: string | null;
+	created_at: string;
+	started_at: string | null;
+	finished_at: string | null;
+};
+
+function triggerUrl(name?: string, suffix = "") {
+	const path = name
+		? `/api/me/triggers/${encodeURIComponent(name)}`
+		: "/api/me/triggers";
+	return controlPlaneApiUrl(`${path}${suffix}`);
+}
+
+/** List the user's triggers */
+export async function listTriggers(): Promise<AgentTrigger[]> {
+	const res = await authFetch(triggerUrl(), { credentials: "include" });
+	if (!res.ok) throw new Error(await readApiError(res));
+	return res.json();
+}
+
+/** Create a trigger; the returned token is not shown again */
+export async function createTrigger(
+	request: SaveTriggerRequest,
+): Promise<AgentTriggerWithToken> {
+	const res = await authFetch(triggerUrl(), {
+		method: "POST",
+		headers: { "Content-Type": "application/json" },
+		body: JSON.stringify(request),
+		credentials: "include",
+	});
+	if (!res.ok) throw new Error(await readApiError(res));
+	return res.json();
+}
+
+/** Replace a trigger's definition (its token stays valid) */
+export async function updateTrigger(
+	name: string,
+	request: SaveTriggerRequest,
+): Promise<AgentTrigger> {
+	const res = await authFetch(triggerUrl(name), {
+		method: "PUT",
+		headers: { "Content-Type": "application/json" },
+		body: JSON.stringify(request),
+		credentials: "include",
+	});
+	if (!res.ok) throw new Error(await readApiError(res));
+	return res.json();
+}
+
+/** Delete a trigger and its run history */
+export async function deleteTrigger(name: string): Promise<void> {
+	const res = await authFetch(triggerUrl(name), {
+		method: "DELETE",
+		credentials: "include",
+	});
+	if (!res.ok) throw new Error(await readApiError(res));
+}
+
+/** Issue a new token; the old one stops working */
+export async function rotateTriggerToken(
+	name: string,
+): Promise<AgentTriggerWithToken> {
+	const res = await authFetch(triggerUrl(name, "/token"), {
+		method: "POST",
+		credentials: "include",
+	});
+	if (!res.ok) throw new Error(await readApiError(res));
+	return res.json();
+}
+
+/** Runs of a trigger, newest first */
+export async function listTriggerRuns(
+	name: string,
+	limit?: number,
+): Promise<TriggerRun[]> {
+	const qs = limit !== undefined ? `?limit=${limit}` : "";
+	const res = await authFetch(triggerUrl(name, `/runs${qs}`), {
+		credentials: "include",
+	});
+	if (!res.ok) throw new Error(await readApiError(res));
+	return res.json();
+}
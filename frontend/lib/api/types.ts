@@ -39,13 +39,18 @@ export type RegisterRequest = {
 	username: string;
 	email: string;
 	password: string;
-	invite_code: string;
+	/** Required unless open registration is enabled. */
+	invite_code?: string;
 	display_name?: string;
 };
 
 export type RegisterResponse = {
-	token: string;
+	/** Missing while the account waits for admin approval. */
+	token?: string;
 	user: UserInfo;
+	status?: "pending_approval";
+	/** Whether the account is quarantined until the email is verified. */
+	email_verification_pending: boolean;
 };
 
 // ============================================================================
@@ -173,6 +178,22 @@ export type ProjectEntry = {
 	logo?: ProjectLogo;
 };
 
+/** Project grid card, derived from the project's README and files */
+export type ProjectCard = {
+	path: string;
+	name: string;
+	/** First heading of the README */
+	title: string | null;
+	/** Opening paragraph of the README, as plain text */
+	description: string | null;
+	/** Language with the most source bytes */
+	language: string | null;
+	/** Newest modification time of a project file (RFC 3339) */
+	last_activity: string | null;
+	/** Thumbnail path relative to the project, see getProjectThumbnailUrl */
+	thumbnail: string | null;
+};
+
 export type WorkspaceMeta = {
 	display_name?: string | null;
 };
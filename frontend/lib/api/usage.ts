@@ -0,0 +1,123 @@
+/**
+ * Usage API
+ * Token counts and spend per session and model, for cost dashboards
+ */
+
+import { authFetch, controlPlaneApiUrl, readApiError } from "./client";
+
+export type UsageBucket = "day" | "week" | "month";
+
+/** Token counts and spend summed over a set of assistant messages */
+export type UsageTotals = {
+	messages: number;
+	input_tokens: number;
+	output_tokens: number;
+	cache_read_tokens: number;
+	cache_write_tokens: number;
+	cost_usd: number;
+};
+
+/** Totals for one model (`provider/model`), session, user or time bucket */
+export type UsageGroup = UsageTotals & {
+	key: string;
+};
+
+export type UsageSummary = {
+	since: string;
+	until: string;
+	bucket: UsageBucket;
+	totals: UsageTotals;
+	/** Oldest bucket first; buckets without usage are omitted */
+	timeline: UsageGroup[];
+	/** Most expensive first */
+	by_model: UsageGroup[];
+	/** Most expensive first */
+	by_session: UsageGroup[];
+	/** Admin summaries only */
+	by_user?: UsageGroup[];
+	/** All-time spend EAVS billed to the user's keys, absent without EAVS */
+	eavs_billed_usd?: number;
+};
+
+export type SessionUsage = {
+	session_id: string;
+	/** Unix milliseconds */
+	first_at: number;
+	last_at: number;
+	totals: UsageTotals;
+	by_model: UsageGroup[];
+	/** One bucket per UTC day */
+	timeline: UsageGroup[];
+};
+
+/** Highest budget threshold a session's spend reached */
+export type BudgetLevel = "ok" | "warning" | "exceeded";
+
+export type SessionBudget = {
+	session_id: string;
+	user_id: string;
+	spent_usd: number;
+	/** Budget in effect; null when the session is unlimited */
+	limit_usd: number | null;
+	/** Whether an admin set the budget for this session */
+	custom_limit: boolean;
+	level: BudgetLevel;
+	/** Whether new prompts are refused */
+	suspended: boolean;
+};
+
+export type UsageSummaryQuery = {
+	/** First day to include (YYYY-MM-DD), defaults to 30 days before `until` */
+	since?: string;
+	/** Last day to include (YYYY-MM-DD), defaults to today */
+	until?: string;
+	bucket?: UsageBucket;
+	/** Sessions listed in `by_session` */
+	limit?: number;
+};
+
+/** Spend of the current user per model, session and time bucket */
+export async function getUsageSummary(
+	query: UsageSummaryQuery = {},
+): Promise<UsageSummary> {
+	const params = new URLSearchParams();
+	if (query.since) params.set("since", query.since);
+	if (query.until) params.set("until", query.until);
+	if (query.bucket) params.set("bucket", query.bucket);
+	if (query.limit) params.set("limit", query.limit.toString());
+	const qs = params.toString();
+	const res = await authFetch(
+		controlPlaneApiUrl(`/api/usage/summary${qs ? `?${qs}` : ""}`),
+		{ credentials: "include" },
+	);
+	if (!res.ok) throw new Error(await readApiError(res));
+	return res.json();
+}
+
+/** Spend of one of the current user's sessions; null if none was recorded */
+export async function getSessionUsage(
+	sessionId: string,
+): Promise<SessionUsage | null> {
+	const res = await authFetch(
+		controlPlaneApiUrl(`/api/usage/sessions/${encodeURIComponent(sessionId)}`),
+		{ credentials: "include" },
+	);
+	if (res.status === 404) return null;
+	if (!res.ok) throw new Error(await readApiError(res));
+	return res.json();
+}
+
+/** Budget state of one of the current user's sessions; null if it has no spend */
+export async function getSessionBudget(
+	sessionId: string,
+): Promise<SessionBudget | null> {
+	const res = await authFetch(
+		controlPlaneApiUrl(
+			`/api/usage/sessions/${encodeURIComponent(sessionId)}/budget`,
+		),
+		{ credentials: "include" },
+	);
+	if (res.status === 404) return null;
+	if (!res.ok) throw new Error(await readApiError(res));
+	return res.json();
+}
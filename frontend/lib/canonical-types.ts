@@ -216,6 +216,10 @@ export type ToolCallInfo = {
 /** Reason for compaction. */
 export type CompactReason = "threshold" | "overflow";
 
+/** How an interjection reaches the agent: steered into the running turn or
+ * held until it ends. */
+export type InterjectionDelivery = "live" | "queued";
+
 /** Notification severity level. */
 export type NotifyLevel = "info" | "warning" | "error";
 
@@ -266,6 +270,14 @@ export type EventPayload =
 			recoverable: boolean;
 			phase?: AgentPhase;
 	  }
+	| {
+			event: "agent.interjection";
+			interjection_id: string;
+			message: string;
+			delivery: InterjectionDelivery;
+			client_id?: string;
+	  }
+	| { event: "agent.interjection_delivered"; interjection_ids: string[] }
 	| { event: "agent.input_needed"; request: InputRequest }
 	| { event: "agent.input_resolved"; request_id: string }
 	// Streaming
@@ -313,6 +325,15 @@ export type EventPayload =
 			name: string;
 			partial_output: unknown;
 	  }
+	| {
+			event: "tool.output_delta";
+			tool_call_id: string;
+			name: string;
+			channel: "stdout" | "stderr";
+			seq: number;
+			delta: string;
+			truncated?: boolean;
+	  }
 	| {
 			event: "tool.end";
 			tool_call_id: string;
@@ -321,6 +342,25 @@ export type EventPayload =
 			is_error: boolean;
 			duration_ms?: number;
 	  }
+	| {
+			event: "tool.approval_required";
+			approval_id: string;
+			tool_call_id: string;
+			name: string;
+			input: unknown;
+			/** Approval rule the call matched */
+			rule: string;
+			/** Unix ms; the call is denied after this */
+			expires_at: number;
+	  }
+	| {
+			event: "tool.approval_resolved";
+			approval_id: string;
+			tool_call_id: string;
+			approved: boolean;
+			reason?: string;
+			timed_out?: boolean;
+	  }
 	// Auto-recovery
 	| {
 			event: "retry.start";
@@ -347,6 +387,38 @@ export type EventPayload =
 	// Config changes
 	| { event: "config.model_changed"; provider: string; model_id: string }
 	| { event: "config.thinking_level_changed"; level: string }
+	// Budget (emitted by the backend)
+	| { event: "budget.warning"; spent_usd: number; limit_usd: number }
+	| {
+			event: "budget.exceeded";
+			spent_usd: number;
+			limit_usd: number;
+			suspended: boolean;
+	  }
+	// Resources (emitted by the runner)
+	| {
+			event: "resource.oom_killed";
+			oom_kills: number;
+			memory_limit_mb?: number;
+	  }
+	// Network (emitted by the runner or, for containers, the backend)
+	| {
+			event: "network.blocked";
+			host?: string;
+			address: string;
+			port: number;
+			reason: string;
+	  }
+	// Image builds (emitted by the backend)
+	| { event: "image.build_started"; build_id: string; image: string }
+	| { event: "image.build_output"; build_id: string; line: string }
+	| {
+			event: "image.build_finished";
+			build_id: string;
+			image: string;
+			success: boolean;
+			error?: string;
+	  }
 	// Notifications
 	| { event: "notify"; level: NotifyLevel; message: string }
 	| { event: "status"; key: string; text: string | null }
@@ -383,6 +455,9 @@ export type Event = {
 	session_id: string;
 	runner_id: string;
 	ts: number;
+	/** Position in the session's replay log; pass the last one seen as
+	 *  `last_seen_seq` when re-creating the session after a reconnect. */
+	seq?: number;
 } & EventPayload;
 
 // ============================================================================
@@ -407,7 +482,7 @@ export type ImageAttachment = {
 /** All canonical command payloads. Tagged union on `cmd` field. */
 export type CommandPayload =
 	// Session lifecycle
-	| { cmd: "session.create"; config: SessionConfig }
+	| { cmd: "session.create"; config: SessionConfig; last_seen_seq?: number }
 	| { cmd: "session.close" }
 	| { cmd: "session.new"; parent_session?: string }
 	| { cmd: "session.switch"; session_path: string }
@@ -416,6 +491,7 @@ export type CommandPayload =
 	| { cmd: "prompt"; message: string; images?: ImageAttachment[] }
 	| { cmd: "steer"; message: string }
 	| { cmd: "follow_up"; message: string }
+	| { cmd: "interject"; message: string }
 	| { cmd: "abort" }
 	| {
 			cmd: "input_response";
@@ -1,4 +1,4 @@
-import { controlPlaneApiUrl } from "@/lib/api/client";
+import { authFetch, controlPlaneApiUrl, readApiError } from "@/lib/api/client";
 import { getWsManager } from "@/lib/ws-manager";
 import type { FileTreeNode, FilesWsEvent } from "@/lib/ws-mux-types";
 
@@ -372,6 +372,40 @@ export async function downloadZipMux(
 	triggerBrowserDownload(url.toString(), zipName);
 }
 
+/** Dry run of a ZIP download, after the workspace's exclusion rules. */
+export type ZipEstimate = {
+	files: number;
+	bytes: number;
+	/** Files left out by `.oqtoignore` and admin globs */
+	excluded_files: number;
+	excluded_bytes: number;
+	rules: string[];
+	excluded_count: number;
+	excluded: { path: string; is_dir: boolean; rule: string }[];
+	/** Fileserver zip limits (0 = no limit) */
+	max_bytes: number;
+	max_entries: number;
+	within_limits: boolean;
+};
+
+/**
+ * Estimate the ZIP archive of `paths` without creating it.
+ */
+export async function estimateZipMux(
+	workspacePath: string,
+	paths: string[],
+): Promise<ZipEstimate> {
+	const url = new URL(
+		controlPlaneApiUrl("/api/workspace/files/download-zip/estimate"),
+		window.location.origin,
+	);
+	url.searchParams.set("workspace_path", workspacePath);
+	url.searchParams.set("paths", paths.join(","));
+	const res = await authFetch(url.toString(), { credentials: "include" });
+	if (!res.ok) throw new Error(await readApiError(res));
+	return res.json();
+}
+
 export async function downloadFileMux(
 	workspacePath: string,
 	path: string,
@@ -380,16 +414,175 @@ export async function downloadFileMux(
 	await downloadPathMux(workspacePath, path, filename);
 }
 
+/** Files above this size use the resumable chunked upload API. */
+export const RESUMABLE_UPLOAD_THRESHOLD = 64 * 1024 * 1024;
+
+/** Attempts per chunk before a resumable upload gives up. */
+const CHUNK_ATTEMPTS = 4;
+
+type ResumableUploadStatus = {
+	id: string;
+	path: string;
+	size: number;
+	/** Bytes received so far; the next chunk starts here */
+	offset: number;
+	/** Suggested chunk size in bytes */
+	chunk_size: number;
+	sha256?: string;
+};
+
+export type ResumableUploadOptions = {
+	/** Id of an interrupted upload to continue */
+	uploadId?: string;
+	/** Called when the server assigns an upload id, to resume it later */
+	onUploadId?: (uploadId: string) => void;
+};
+
 export async function uploadFileMux(
 	workspacePath: string,
 	destPath: string,
 	file: File,
 	onProgress?: (loaded: number, total: number) => void,
 	signal?: AbortSignal,
+	resumable?: ResumableUploadOptions,
 ): Promise<void> {
+	if (file.size > RESUMABLE_UPLOAD_THRESHOLD) {
+		await uploadFileResumable(
+			workspacePath,
+			destPath,
+			file,
+			onProgress,
+			signal,
+			resumable,
+		);
+		return;
+	}
 	await uploadFileHttp(workspacePath, destPath, file, onProgress, signal);
 }
 
+function uploadsUrl(
+	workspacePath: string,
+	suffix: string,
+	params: Record<string, string> = {},
+): string {
+	const url = new URL(
+		controlPlaneApiUrl(`/api/workspace/files/uploads${suffix}`),
+		window.location.origin,
+	);
+	url.searchParams.set("workspace_path", workspacePath);
+	for (const [key, value] of Object.entries(params)) {
+		url.searchParams.set(key, value);
+	}
+	return url.toString();
+}
+
+/** Hex SHA-256 of `data`, or undefined where WebCrypto is unavailable. */
+async function sha256Hex(data: ArrayBuffer): Promise<string | undefined> {
+	if (typeof globalThis.crypto?.subtle === "undefined") return undefined;
+	const digest = await globalThis.crypto.subtle.digest("SHA-256", data);
+	return Array.from(new Uint8Array(digest), (b) =>
+		b.toString(16).padStart(2, "0"),
+	).join("");
+}
+
+/**
+ * Upload a file in chunks through the fileserver's resumable upload API.
+ * Failed chunks are retried from the offset the server reports, each chunk
+ * carries its SHA-256, and an interrupted upload continues where it stopped
+ * when its id is passed back in `options.uploadId`.
+ */
+export async function uploadFileResumable(
+	workspacePath: string,
+	destPath: string,
+	file: File,
+	onProgress?: (loaded: number, total: number) => void,
+	signal?: AbortSignal,
+	options: ResumableUploadOptions = {},
+): Promise<void> {
+	const request = async (url: string, init: RequestInit = {}) => {
+		try {
+			return await authFetch(url, { ...init, credentials: "include", signal });
+		} catch (error) {
+			if (signal?.aborted) throw new Error("Upload cancelled");
+			throw error;
+		}
+	};
+	const fetchStatus = async (id: string) => {
+		const res = await request(uploadsUrl(workspacePath, `/${id}`));
+		if (!res.ok) throw new Error(await readApiError(res));
+		return (await res.json()) as ResumableUploadStatus;
+	};
+
+	let status: ResumableUploadStatus | null = null;
+	if (options.uploadId) {
+		// An expired or unknown upload starts over.
+		status = await fetchStatus(options.uploadId).catch(() => null);
+		if (status && status.size !== file.size) status = null;
+	}
+	if (!status) {
+		const res = await request(
+			uploadsUrl(workspacePath, "", { path: destPath, mkdir: "true" }),
+			{
+				method: "POST",
+				headers: { "Content-Type": "application/json" },
+				body: JSON.stringify({ size: file.size }),
+			},
+		);
+		if (!res.ok) throw new Error(await readApiError(res));
+		status = (await res.json()) as ResumableUploadStatus;
+		options.onUploadId?.(status.id);
+	}
+
+	const id = status.id;
+	const chunkSize = Math.max(1, status.chunk_size);
+	let offset = status.offset;
+	let failures = 0;
+	onProgress?.(offset, file.size);
+	while (offset < file.size) {
+		const chunk = await file.slice(offset, offset + chunkSize).arrayBuffer();
+		const headers: Record<string, string> = {
+			"Content-Type": "application/octet-stream",
+		};
+		const digest = await sha256Hex(chunk);
+		if (digest) headers["X-Chunk-Sha256"] = digest;
+
+		let error: string;
+		try {
+			const res = await request(
+				uploadsUrl(workspacePath, `/${id}`, { offset: String(offset) }),
+				{ method: "PATCH", headers, body: chunk },
+			);
+			if (res.ok) {
+				offset = ((await res.json()) as ResumableUploadStatus).offset;
+				failures = 0;
+				onProgress?.(offset, file.size);
+				continue;
+			}
+			// Offset conflicts and corrupted chunks are retried; other
+			// errors (quota, permissions) are final.
+			error = await readApiError(res);
+			if (res.status !== 409 && res.status !== 422 && res.status < 500) {
+				throw new Error(`Upload failed (${res.status}): ${error}`);
+			}
+		} catch (err) {
+			if (signal?.aborted || !(err instanceof TypeError)) throw err;
+			error = "network error";
+		}
+		failures += 1;
+		if (failures >= CHUNK_ATTEMPTS) {
+			throw new Error(`Upload failed: ${error}`);
+		}
+		// The chunk may have been stored before the connection dropped.
+		offset = (await fetchStatus(id).catch(() => null))?.offset ?? offset;
+	}
+
+	const res = await request(uploadsUrl(workspacePath, `/${id}/complete`), {
+		method: "POST",
+	});
+	if (!res.ok) throw new Error(await readApiError(res));
+	onProgress?.(file.size, file.size);
+}
+
 export async function uploadFileHttp(
 	workspacePath: string,
 	destPath: string,
@@ -16,6 +16,8 @@ export type UploadJob = {
 	loaded: number;
 	status: UploadStatus;
 	error?: string;
+	/** Server-side id of a resumable upload; retrying continues it */
+	uploadId?: string;
 	createdAt: number;
 	updatedAt: number;
 };
@@ -107,7 +109,8 @@ class UploadManager {
 		this.clearDoneCleanup(id);
 		job.status = "queued";
 		job.error = undefined;
-		job.loaded = 0;
+		// Resumable uploads pick up at the offset the server reports.
+		if (!job.uploadId) job.loaded = 0;
 		job.updatedAt = Date.now();
 		const file = this.fileBlobs.get(id);
 		if (!file) {
@@ -151,6 +154,15 @@ class UploadManager {
 					void this.saveJob(current);
 				},
 				abortController.signal,
+				{
+					uploadId: job.uploadId,
+					onUploadId: (uploadId) => {
+						const current = this.jobs.get(next.id);
+						if (!current) return;
+						current.uploadId = uploadId;
+						void this.saveJob(current);
+					},
+				},
 			)
 				.then(() => {
 					const current = this.jobs.get(next.id);
@@ -136,6 +136,8 @@ class WsConnectionManager {
 	private sessionCreateInFlight: Set<string> = new Set();
 	private sessionCreateTimers: Map<string, ReturnType<typeof setTimeout>> =
 		new Map();
+	// Last agent event seq per session, sent on reconnect to replay the gap
+	private lastSeenSeq: Map<string, number> = new Map();
 	// Pending messages to send once session is ready
 	private pendingMessages: Map<
 		string,
@@ -882,6 +884,7 @@ class WsConnectionManager {
 		this.clearOutboxForSession(sessionId);
 		this.agentSessionHandlers.delete(sessionId);
 		this.resyncHandlers.delete(sessionId);
+		this.lastSeenSeq.delete(sessionId);
 
 		this.send({
 			channel: "agent",
@@ -1240,6 +1243,7 @@ class WsConnectionManager {
 					session_id: sessionId,
 					cmd: "session.create",
 					config: entry.config ?? {},
+					last_seen_seq: this.lastSeenSeq.get(sessionId),
 				});
 			}
 			this.pendingSubscriptions.clear();
@@ -1268,6 +1272,7 @@ class WsConnectionManager {
 					session_id: sessionId,
 					cmd: "session.create",
 					config: entry.config ?? {},
+					last_seen_seq: this.lastSeenSeq.get(sessionId),
 				});
 			}
 
@@ -1277,6 +1282,7 @@ class WsConnectionManager {
 					this.subscribedSessions.delete(sessionId);
 					this.sessionReady.delete(sessionId);
 					this.pendingMessages.delete(sessionId);
+					this.lastSeenSeq.delete(sessionId);
 					this.clearSessionCreateInFlight(sessionId);
 				}
 			}
@@ -1368,6 +1374,9 @@ class WsConnectionManager {
 		// Track agent session readiness via response to session.create command
 		if (event.channel === "agent") {
 			const agentEvent = event as AgentWsEvent;
+			if (typeof agentEvent.seq === "number") {
+				this.lastSeenSeq.set(agentEvent.session_id, agentEvent.seq);
+			}
 
 			// Check if this is a successful session.create response.
 			// CommandResponse fields are flattened into the top-level event:
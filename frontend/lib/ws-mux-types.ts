@@ -601,9 +601,23 @@ export type SharedWorkspaceChangeType =
 	| "workspace_updated"
 	| "workspace_deleted";
 
+/** State of an optional backend subsystem */
+export type SubsystemCapability = {
+	subsystem: "mmry" | "voice" | "hstry" | "eavs" | "sldr";
+	state: "enabled" | "disabled" | "degraded";
+	/** Why the subsystem is disabled or degraded */
+	reason?: string;
+	/** When the subsystem entered this state */
+	since: string;
+};
+
 /** System channel events */
 export type SystemWsEvent =
-	| ({ channel: "system"; type: "connected" } & WsEventBase)
+	| ({
+			channel: "system";
+			type: "connected";
+			capabilities: SubsystemCapability[];
+	  } & WsEventBase)
 	| ({ channel: "system"; type: "error"; error: string } & WsEventBase)
 	| ({ channel: "system"; type: "ping" } & WsEventBase)
 	| ({
@@ -612,6 +626,14 @@ export type SystemWsEvent =
 			workspace_id: string;
 			change_type: SharedWorkspaceChangeType;
 			detail: string | null;
+	  } & WsEventBase)
+	| ({
+			channel: "system";
+			type: "voice.availability";
+			/** Whether the STT and TTS services are reachable */
+			available: boolean;
+			/** Why voice is unavailable */
+			reason?: string;
 	  } & WsEventBase);
 
 /** All possible WebSocket events */
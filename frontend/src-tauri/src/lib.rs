@@ -1,5 +1,7 @@
+use mdns_sd::{ServiceDaemon, ServiceEvent};
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use std::net::IpAddr;
 use std::time::Duration;
 use tauri::{AppHandle, Emitter};
 use tokio::time::timeout;
@@ -197,97 +199,97 @@ async fn http_delete(
     Ok(HttpResponse { status, data, ok })
 }
 
-#[tauri::command]
-async fn discover_servers() -> Result<Vec<DiscoveredServer>, String> {
-    let mut discovered = Vec::new();
+/// mDNS service type advertised by `oqto serve`.
+const SERVICE_TYPE: &str = "_oqto._tcp.local.";
 
-    // Get local network ranges to scan
-    let local_ips = get_local_network_ranges().await?;
+/// How long to browse when the caller does not say.
+const DEFAULT_DISCOVERY_MS: u64 = 1500;
 
-    // Common opencode ports to check
-    let ports = vec![4096, 3000, 8080, 8000, 3001, 8001];
+/// Find oqto servers on the local network by browsing for their mDNS
+/// advertisement. Collects answers for `timeout_ms` and returns one entry
+/// per server.
+#[tauri::command]
+async fn discover_servers(timeout_ms: Option<u64>) -> Result<Vec<DiscoveredServer>, String> {
+    let daemon = ServiceDaemon::new().map_err(|e| format!("mDNS daemon error: {}", e))?;
+    let receiver = daemon
+        .browse(SERVICE_TYPE)
+        .map_err(|e| format!("mDNS browse error: {}", e))?;
 
-    for network in local_ips {
-        for port in &ports {
-            let servers = scan_network_range(network.clone(), *port).await;
-            discovered.extend(servers);
+    let start = std::time::Instant::now();
+    let window = Duration::from_millis(timeout_ms.unwrap_or(DEFAULT_DISCOVERY_MS));
+    let mut servers: HashMap<String, DiscoveredServer> = HashMap::new();
+
+    while let Some(remaining) = window.checked_sub(start.elapsed()) {
+        match timeout(remaining, receiver.recv_async()).await {
+            Ok(Ok(ServiceEvent::ServiceResolved(info))) => {
+                let Some(host) = preferred_address(info.get_addresses(), info.get_hostname())
+                else {
+                    continue;
+                };
+                let name = info
+                    .get_property_val_str("name")
+                    .map(str::to_string)
+                    .unwrap_or_else(|| instance_name(info.get_fullname()));
+                log::info!(
+                    "[discover_servers] Found {} at {}:{}",
+                    name,
+                    host,
+                    info.get_port()
+                );
+                servers.insert(
+                    info.get_fullname().to_string(),
+                    DiscoveredServer {
+                        host,
+                        port: info.get_port(),
+                        name,
+                        version: info.get_property_val_str("version").map(str::to_string),
+                        response_time_ms: start.elapsed().as_millis() as u64,
+                    },
+                );
+            }
+            Ok(Ok(ServiceEvent::ServiceRemoved(_, fullname))) => {
+                servers.remove(&fullname);
+            }
+            Ok(Ok(_)) => {}
+            // Window elapsed or the daemon stopped.
+            Ok(Err(_)) | Err(_) => break,
         }
     }
 
-    Ok(discovered)
-}
+    let _ = daemon.stop_browse(SERVICE_TYPE);
+    let _ = daemon.shutdown();
 
-async fn get_local_network_ranges() -> Result<Vec<String>, String> {
-    // For now, scan common local network ranges
-    // In a more sophisticated implementation, we'd get the actual network interfaces
-    Ok(vec![
-        "192.168.1".to_string(),
-        "192.168.0".to_string(),
-        "10.0.0".to_string(),
-        "172.16.0".to_string(),
-    ])
+    let mut servers: Vec<_> = servers.into_values().collect();
+    servers.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(servers)
 }
 
-async fn scan_network_range(network_prefix: String, port: u16) -> Vec<DiscoveredServer> {
-    let mut servers = Vec::new();
-    let mut tasks = Vec::new();
-
-    // Scan first 20 IPs in the range (to keep it fast)
-    for i in 1..=20 {
-        let host = format!("{}.{}", network_prefix, i);
-        let task = check_opencode_server(host, port);
-        tasks.push(task);
+/// Host to reach a discovered server at: IPv4 first, then a routable IPv6
+/// address. Link-local IPv6 addresses need a zone to be usable, which the
+/// resolved record does not carry, so on IPv6-only links the advertised
+/// `.local` host name is returned for the system resolver to look up.
+fn preferred_address(addresses: &HashSet<IpAddr>, hostname: &str) -> Option<String> {
+    let routable_v6 = |ip: &&IpAddr| match ip {
+        IpAddr::V6(v6) => !v6.is_unicast_link_local(),
+        IpAddr::V4(_) => false,
+    };
+    if let Some(ip) = addresses.iter().find(|ip| ip.is_ipv4()) {
+        return Some(ip.to_string());
     }
-
-    // Wait for all checks to complete with timeout
-    let results = futures::future::join_all(tasks).await;
-
-    for server in results.into_iter().flatten() {
-        servers.push(server);
+    if let Some(ip) = addresses.iter().find(routable_v6) {
+        return Some(format!("[{}]", ip));
     }
-
-    servers
+    let hostname = hostname.trim_end_matches('.');
+    (!addresses.is_empty() && !hostname.is_empty()).then(|| hostname.to_string())
 }
 
-async fn check_opencode_server(host: String, port: u16) -> Option<DiscoveredServer> {
-    let url = format!("http://{}:{}/app", host, port);
-    let start = std::time::Instant::now();
-
-    // Short timeout for discovery
-    let client = reqwest::Client::builder()
-        .timeout(Duration::from_millis(2000))
-        .build()
-        .ok()?;
-
-    if let Ok(Ok(response)) = timeout(Duration::from_millis(2000), client.get(&url).send()).await {
-        let response_time = start.elapsed().as_millis() as u64;
-
-        if response.status().is_success() {
-            // Try to get server info
-            if let Ok(data) = response.json::<serde_json::Value>().await {
-                let name = data
-                    .get("name")
-                    .and_then(|v| v.as_str())
-                    .unwrap_or("OpenCode Server")
-                    .to_string();
-
-                let version = data
-                    .get("version")
-                    .and_then(|v| v.as_str())
-                    .map(|s| s.to_string());
-
-                return Some(DiscoveredServer {
-                    host,
-                    port,
-                    name,
-                    version,
-                    response_time_ms: response_time,
-                });
-            }
-        }
-    }
-
-    None
+/// Instance part of an mDNS full name (`name._oqto._tcp.local.`).
+fn instance_name(fullname: &str) -> String {
+    fullname
+        .strip_suffix(SERVICE_TYPE)
+        .map(|name| name.trim_end_matches('.'))
+        .unwrap_or(fullname)
+        .to_string()
 }
 
 #[tauri::command]
@@ -22,4 +22,9 @@ export type CreateSessionRequest = {
 	 * Environment variables to inject.
 	 */
 	env: { [key in string]?: string };
+	/**
+	 * Sandbox profile whose egress rules apply to the container, nested in
+	 * sandbox.toml so it can only add restrictions.
+	 */
+	sandbox_profile: string | null;
 };
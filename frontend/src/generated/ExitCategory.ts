@@ -0,0 +1,13 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * Kind of failure behind a session exit.
+ */
+export type ExitCategory =
+	| "missing_api_key"
+	| "oom_killed"
+	| "binary_not_found"
+	| "killed"
+	| "crashed"
+	| "exited"
+	| "error";
@@ -0,0 +1,28 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { ExitCategory } from "./ExitCategory";
+
+/**
+ * Structured exit diagnostics of a session.
+ */
+export type ExitInfo = {
+	/**
+	 * Exit code, if the process exited on its own.
+	 */
+	exit_code: number | null;
+	/**
+	 * Signal that killed the process, if any.
+	 */
+	signal: number | null;
+	/**
+	 * Classified failure.
+	 */
+	category: ExitCategory;
+	/**
+	 * Human-readable explanation, e.g. "OOM killed (2GB limit)".
+	 */
+	hint: string;
+	/**
+	 * Service that exited, for local sessions (e.g. "fileserver").
+	 */
+	service: string | null;
+};
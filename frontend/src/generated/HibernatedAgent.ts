@@ -0,0 +1,13 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * An agent session running when its workspace session was hibernated.
+ */
+export type HibernatedAgent = {
+	session_id: string;
+	cwd: string;
+	harness: string | null;
+	provider: string | null;
+	model: string | null;
+	sandbox_profile: string | null;
+};
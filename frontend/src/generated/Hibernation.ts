@@ -0,0 +1,21 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { HibernatedAgent } from "./HibernatedAgent";
+
+/**
+ * State saved when a session was hibernated.
+ */
+export type Hibernation = {
+	/**
+	 * When the session was hibernated.
+	 */
+	hibernated_at: string;
+	/**
+	 * Container checkpoint holding the session's processes (container mode).
+	 */
+	checkpoint: string | null;
+	/**
+	 * Agent sessions that ran in the workspace (local mode). They are
+	 * resumed from their session files.
+	 */
+	agents: Array<HibernatedAgent>;
+};
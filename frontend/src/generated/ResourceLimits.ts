@@ -0,0 +1,23 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * Resource limits of a container. Unset limits use the runtime's default.
+ */
+export type ResourceLimits = {
+	/**
+	 * Relative CPU weight (`--cpu-shares`, 1024 is the runtime default).
+	 */
+	cpu_shares: number | null;
+	/**
+	 * Memory limit in MiB, swap included.
+	 */
+	memory_mb: number | null;
+	/**
+	 * Maximum number of processes.
+	 */
+	pids_limit: number | null;
+	/**
+	 * GPUs to pass through: "all" or comma-separated device indices/UUIDs.
+	 */
+	gpus: string | null;
+};
@@ -1,4 +1,7 @@
 // This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { ExitInfo } from "./ExitInfo";
+import type { Hibernation } from "./Hibernation";
+import type { ResourceLimits } from "./ResourceLimits";
 import type { RuntimeMode } from "./RuntimeMode";
 import type { SessionStatus } from "./SessionStatus";
 
@@ -111,4 +114,17 @@ export type Session = {
 	 * Error message if failed.
 	 */
 	error_message: string | null;
+	/**
+	 * Why the session last stopped, when known. Cleared when it runs again.
+	 */
+	exit_info: ExitInfo | null;
+	/**
+	 * Resource limits of the session's container (container mode only).
+	 */
+	resource_limits: ResourceLimits | null;
+	/**
+	 * State saved when the session was hibernated, restored by the next
+	 * resume.
+	 */
+	hibernation: Hibernation | null;
 };
@@ -0,0 +1,16 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * A host directory mounted into a container session.
+ */
+export type SessionMount = {
+	/**
+	 * Directory on the host; must lie in the user's workspace roots.
+	 */
+	source: string;
+	/**
+	 * Absolute path inside the container.
+	 */
+	target: string;
+	read_only: boolean;
+};
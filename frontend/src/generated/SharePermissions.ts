@@ -0,0 +1,16 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * Capabilities granted to a member of a shared workspace.
+ *
+ * Members without an explicit grant get the defaults of their role
+ * (`for_role`); owners always hold every capability.
+ */
+export type SharePermissions = {
+	chat_read: boolean;
+	chat_write: boolean;
+	terminal_view: boolean;
+	terminal_control: boolean;
+	file_read: boolean;
+	file_write: boolean;
+};
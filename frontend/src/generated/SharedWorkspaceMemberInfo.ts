@@ -1,5 +1,6 @@
 // This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
 import type { MemberRole } from "./MemberRole";
+import type { SharePermissions } from "./SharePermissions";
 
 /**
  * Public member info (returned to clients).
@@ -11,4 +12,12 @@ export type SharedWorkspaceMemberInfo = {
 	avatar_url: string | null;
 	role: MemberRole;
 	added_at: string;
+	/**
+	 * Effective capabilities of this member.
+	 */
+	permissions: SharePermissions;
+	/**
+	 * Whether `permissions` were set explicitly instead of derived from the role.
+	 */
+	custom_permissions: boolean;
 };
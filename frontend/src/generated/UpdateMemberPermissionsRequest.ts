@@ -0,0 +1,12 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { SharePermissions } from "./SharePermissions";
+
+/**
+ * Request to set a member's capabilities.
+ */
+export type UpdateMemberPermissionsRequest = {
+	/**
+	 * New grant; null resets the member to the defaults of their role.
+	 */
+	permissions: SharePermissions | null;
+};
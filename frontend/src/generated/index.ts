@@ -8,6 +8,12 @@ export type { RuntimeMode } from "./RuntimeMode";
 export type { CreateSessionRequest } from "./CreateSessionRequest";
 export type { SessionResponse } from "./SessionResponse";
 export type { SessionUrls } from "./SessionUrls";
+export type { ExitInfo } from "./ExitInfo";
+export type { HibernatedAgent } from "./HibernatedAgent";
+export type { Hibernation } from "./Hibernation";
+export type { ResourceLimits } from "./ResourceLimits";
+export type { SessionMount } from "./SessionMount";
+export type { ExitCategory } from "./ExitCategory";
 
 // User types
 export type { UserInfo } from "./UserInfo";
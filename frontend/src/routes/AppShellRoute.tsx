@@ -18,13 +18,21 @@ import {
 	PanelRightClose,
 } from "lucide-react";
 import { useTheme } from "next-themes";
-import { memo, useCallback, useDeferredValue, useMemo, useState } from "react";
+import {
+	memo,
+	useCallback,
+	useDeferredValue,
+	useEffect,
+	useMemo,
+	useState,
+} from "react";
 import { useTranslation } from "react-i18next";
 import { useLocation, useNavigate } from "react-router-dom";
 import "@/apps";
 import { UIControlProvider } from "@/components/contexts/ui-control-context";
 
 import type { SearchMode } from "@/components/search";
+import { getBookmark } from "@/lib/api/bookmarks";
 import { type ChatSession, triggerChatHistoryBackfill } from "@/lib/api/chat";
 import {
 	type SharedWorkspaceInfo,
@@ -558,6 +566,46 @@ const AppShell = memo(function AppShell() {
 		],
 	);
 
+	// Open bookmark deep links (`/sessions?bookmark={id}`) at the bookmarked message.
+	const bookmarkParam = new URLSearchParams(location.search).get("bookmark");
+	// biome-ignore lint/correctness/useExhaustiveDependencies: only a new link should open, not history refreshes
+	useEffect(() => {
+		if (!bookmarkParam) return;
+		let cancelled = false;
+		getBookmark(bookmarkParam)
+			.then((bookmark) => {
+				if (cancelled) return;
+				const existingSession = chatHistory.find(
+					(s) => s.id === bookmark.session_id,
+				);
+				if (!existingSession) {
+					const sharedWorkspace = bookmark.workspace_id
+						? sharedWs.sharedWorkspaces.find(
+								(ws) => ws.id === bookmark.workspace_id,
+							)
+						: undefined;
+					createOptimisticChatSession(
+						bookmark.session_id,
+						sharedWorkspace?.path,
+						bookmark.workspace_id ?? undefined,
+					);
+				}
+				setScrollToMessageId(bookmark.message_id);
+				setSelectedChatSessionId(bookmark.session_id);
+				setSelectedWorkspaceOverviewPath(null);
+				setActiveAppId("sessions");
+			})
+			.catch((err) => {
+				console.error("[bookmark] failed to open bookmark", err);
+			})
+			.finally(() => {
+				if (!cancelled) navigate(location.pathname, { replace: true });
+			});
+		return () => {
+			cancelled = true;
+		};
+	}, [bookmarkParam]);
+
 	const handleNewChat = useCallback(async () => {
 		setSelectedWorkspaceOverviewPath(null);
 		if (selectedProjectKey) {
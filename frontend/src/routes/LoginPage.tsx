@@ -26,12 +26,14 @@ import {
 import { Input } from "@/components/ui/input";
 import { authKeys } from "@/hooks/use-auth";
 import {
+	controlPlaneApiUrl,
 	getControlPlaneBaseUrl,
+	getFeatures,
 	login,
 	setControlPlaneBaseUrl,
 } from "@/lib/control-plane-client";
 import { isTauri } from "@/lib/tauri-fetch-polyfill";
-import { useQueryClient } from "@tanstack/react-query";
+import { useQuery, useQueryClient } from "@tanstack/react-query";
 
 /**
  * Detect the backend API URL.
@@ -69,12 +71,21 @@ export function LoginPage() {
 	const queryClient = useQueryClient();
 	const [searchParams] = useSearchParams();
 	const redirectTo = searchParams.get("redirect") || "/";
-	const [error, setError] = useState<string | null>(null);
+	const [error, setError] = useState<string | null>(
+		searchParams.get("sso_error"),
+	);
 	const [isLoading, setIsLoading] = useState(false);
 	// Show backend URL field by default in Tauri (no reverse proxy) or when
 	// nothing was auto-detected
 	const [showAdvanced, setShowAdvanced] = useState(isTauri());
 
+	const { data: features } = useQuery({
+		queryKey: ["features"],
+		queryFn: getFeatures,
+		staleTime: 60_000,
+	});
+	const oidc = features?.oidc;
+
 	const form = useForm<LoginFormData>({
 		resolver: zodResolver(loginSchema),
 		defaultValues: {
@@ -209,6 +220,18 @@ export function LoginPage() {
 						<Button type="submit" className="w-full" disabled={isLoading}>
 							{isLoading ? "Signing in..." : "Sign in"}
 						</Button>
+
+						{oidc && (
+							<Button asChild variant="outline" className="w-full">
+								<a
+									href={controlPlaneApiUrl(
+										`${oidc.login_url}?return_to=${encodeURIComponent(redirectTo)}`,
+									)}
+								>
+									{oidc.label}
+								</a>
+							</Button>
+						)}
 					</form>
 				</Form>
 			</CardContent>
@@ -40,7 +40,7 @@ const registerSchema = z
 		email: z.string().email("Please enter a valid email address"),
 		password: z.string().min(6, "Password must be at least 6 characters"),
 		confirmPassword: z.string(),
-		inviteCode: z.string().min(1, "Invite code is required"),
+		inviteCode: z.string().optional(),
 		displayName: z.string().optional(),
 	})
 	.refine((data) => data.password === data.confirmPassword, {
@@ -134,6 +134,7 @@ export function RegisterPage() {
 	const navigate = useNavigate();
 	const queryClient = useQueryClient();
 	const [error, setError] = useState<string | null>(null);
+	const [notice, setNotice] = useState<string | null>(null);
 	const [isLoading, setIsLoading] = useState(false);
 	const [isProvisioning, setIsProvisioning] = useState(false);
 	const provisioningStartRef = useRef(0);
@@ -161,10 +162,20 @@ export function RegisterPage() {
 				username: data.username,
 				email: data.email,
 				password: data.password,
-				invite_code: data.inviteCode,
+				invite_code: data.inviteCode || undefined,
 				display_name: data.displayName || undefined,
 			});
 
+			if (!result.token) {
+				setNotice(
+					"Your account was created and is waiting for approval by an administrator." +
+						(result.email_verification_pending
+							? " Please confirm your email address with the link we sent you."
+							: ""),
+				);
+				return;
+			}
+
 			// Seed the auth cache so RequireAuth doesn't redirect to login
 			if (result.user) {
 				queryClient.setQueryData(authKeys.me(), result.user);
@@ -217,6 +228,11 @@ export function RegisterPage() {
 									<AlertDescription>{error}</AlertDescription>
 								</Alert>
 							)}
+							{notice && (
+								<Alert>
+									<AlertDescription>{notice}</AlertDescription>
+								</Alert>
+							)}
 
 							<FormField
 								control={form.control}
@@ -226,7 +242,7 @@ export function RegisterPage() {
 										<FormLabel>Invite Code</FormLabel>
 										<FormControl>
 											<Input
-												placeholder="Enter your invite code"
+												placeholder="Enter your invite code, if you have one"
 												disabled={isLoading}
 												{...field}
 											/>
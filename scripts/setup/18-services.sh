@@ -109,6 +109,7 @@ ExecStart=${oqto_runner_bin} --socket %t/oqto-runner.sock
 Restart=on-failure
 RestartSec=5
 TimeoutStartSec=30
+Delegate=yes
 Environment=RUST_LOG=info
 Environment=PATH=%h/.bun/bin:%h/.cargo/bin:%h/.local/bin:/usr/local/bin:/usr/bin:/bin
 Environment=HOME=%h